@@ -51,6 +51,28 @@ impl Bio {
             complete_fn,
             status: AtomicU32::new(BioStatus::Init as u32),
             wait_queue: WaitQueue::new(),
+            remapped_from: None,
+        });
+        Self(inner)
+    }
+
+    /// Constructs a new `Bio` that carries no data.
+    ///
+    /// This is used by the I/O that only describes a range of sectors, e.g., discarding the
+    /// sectors or writing zeroes to them.
+    pub fn new_dataless(
+        type_: BioType,
+        sid_range: Range<Sid>,
+        complete_fn: Option<fn(&SubmittedBio)>,
+    ) -> Self {
+        let inner = Arc::new(BioInner {
+            type_,
+            sid_range,
+            segments: Vec::new(),
+            complete_fn,
+            status: AtomicU32::new(BioStatus::Init as u32),
+            wait_queue: WaitQueue::new(),
+            remapped_from: None,
         });
         Self(inner)
     }
@@ -286,6 +308,30 @@ impl SubmittedBio {
             complete_fn(self);
         }
     }
+
+    /// Remaps the `Bio` to the sectors that are `nsectors` after the original ones.
+    ///
+    /// The remapped `Bio` shares the memory segments with the original one. When it is
+    /// completed, the original one is completed with the same status. This is used by the block
+    /// devices that forward the requests to other block devices (e.g., the partitions).
+    pub(crate) fn remap(&self, nsectors: u64) -> Bio {
+        let sid_range = self.sid_range();
+        let inner = Arc::new(BioInner {
+            type_: self.type_(),
+            sid_range: sid_range.start + nsectors..sid_range.end + nsectors,
+            segments: self.segments().to_vec(),
+            complete_fn: Some(complete_remapped_bio),
+            status: AtomicU32::new(BioStatus::Init as u32),
+            wait_queue: WaitQueue::new(),
+            remapped_from: Some(SubmittedBio(self.0.clone())),
+        });
+        Bio(inner)
+    }
+}
+
+fn complete_remapped_bio(bio: &SubmittedBio) {
+    let original_bio = bio.0.remapped_from.as_ref().unwrap();
+    original_bio.complete(bio.status());
 }
 
 /// The common inner part of `Bio`.
@@ -302,6 +348,8 @@ struct BioInner {
     status: AtomicU32,
     /// The wait queue for I/O completion
     wait_queue: WaitQueue,
+    /// The original `Bio` if this `Bio` is remapped from it
+    remapped_from: Option<SubmittedBio>,
 }
 
 impl BioInner {
@@ -346,6 +394,15 @@ pub enum BioType {
     Flush = 2,
     /// Discard sectors.
     Discard = 3,
+    /// Write zeroes to sectors.
+    WriteZeroes = 4,
+}
+
+impl BioType {
+    /// Returns whether the bios of this type transfer data with their segments.
+    pub fn has_data(self) -> bool {
+        matches!(self, Self::Read | Self::Write)
+    }
 }
 
 /// The status of `Bio`.
@@ -364,6 +421,8 @@ pub enum BioStatus {
     NoSpace = 4,
     /// An error occurred while doing I/O.
     IoError = 5,
+    /// The I/O operation did not complete before its deadline.
+    TimedOut = 6,
 }
 
 /// `BioSegment` is the basic memory unit of a block I/O request.
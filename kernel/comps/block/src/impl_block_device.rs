@@ -89,6 +89,57 @@ impl dyn BlockDevice {
         let status = bio.submit_and_wait(self)?;
         Ok(status)
     }
+
+    /// Synchronously discards the sectors in the `sid_range`.
+    ///
+    /// The range is split into several bios if it exceeds the limit of the device. If the
+    /// device does not support discarding the sectors, `BioStatus::NotSupported` is returned.
+    pub fn discard(&self, sid_range: Range<Sid>) -> Result<BioStatus, BioEnqueueError> {
+        self.submit_dataless(BioType::Discard, sid_range, self.max_discard_sectors())
+    }
+
+    /// Synchronously writes zeroes to the sectors in the `sid_range`.
+    ///
+    /// The range is split into several bios if it exceeds the limit of the device. If the
+    /// device does not support writing zeroes without data, `BioStatus::NotSupported` is
+    /// returned.
+    pub fn write_zeroes(&self, sid_range: Range<Sid>) -> Result<BioStatus, BioEnqueueError> {
+        self.submit_dataless(
+            BioType::WriteZeroes,
+            sid_range,
+            self.max_write_zeroes_sectors(),
+        )
+    }
+
+    fn submit_dataless(
+        &self,
+        type_: BioType,
+        sid_range: Range<Sid>,
+        max_nsectors: usize,
+    ) -> Result<BioStatus, BioEnqueueError> {
+        if max_nsectors == 0 {
+            return Ok(BioStatus::NotSupported);
+        }
+
+        let mut waiter = BioWaiter::new();
+        let mut start = sid_range.start;
+        while start < sid_range.end {
+            let nsectors = (sid_range.end.to_raw() - start.to_raw()).min(max_nsectors as u64);
+            let end = start + nsectors;
+            let bio = Bio::new_dataless(type_, start..end, Some(general_complete_fn));
+            waiter.concat(bio.submit(self)?);
+            start = end;
+        }
+
+        if waiter.wait().is_some() {
+            return Ok(BioStatus::Complete);
+        }
+        let status = (0..waiter.nreqs())
+            .map(|index| waiter.status(index))
+            .find(|status| *status != BioStatus::Complete)
+            .unwrap();
+        Ok(status)
+    }
 }
 
 impl VmIo for dyn BlockDevice {
@@ -9,7 +9,12 @@
 //! for a block device to maintain a queue to handle I/O requests. The users (e.g., fs)
 //! submit I/O requests to this queue and wait for their completion. Drivers implementing
 //! block devices can create their own queues as needed, with the possibility to reorder
-//! and merge requests within the queue.
+//! and merge requests within the queue. The `BioRequestSingleQueue` provides such a queue,
+//! which merges the requests of adjacent sectors and orders them with an I/O scheduler.
+//! Its statistics and tunables are exposed in the `block` class of the device model.
+//!
+//! The partitions of a block device are registered as block devices after the partition table
+//! of the device is scanned with `scan_partitions`.
 //!
 //! This crate also offers the `Bio` related data structures and APIs to accomplish
 //! safe and convenient block I/O operations, for example:
@@ -37,8 +42,14 @@ extern crate alloc;
 pub mod bio;
 pub mod id;
 mod impl_block_device;
+pub mod partition;
 mod prelude;
 pub mod request_queue;
+pub mod scheduler;
+mod sysfs;
+pub mod timeout;
+
+use alloc::format;
 
 use component::{init_component, ComponentInitError};
 use ostd::sync::SpinLock;
@@ -46,18 +57,55 @@ use spin::Once;
 
 use self::{
     bio::{BioEnqueueError, SubmittedBio},
+    partition::{Partition, ScanError},
     prelude::*,
+    request_queue::{BioRequestSingleQueue, QueuePlug},
+    timeout::HungIoStats,
 };
 
 pub const BLOCK_SIZE: usize = ostd::mm::PAGE_SIZE;
 pub const SECTOR_SIZE: usize = 512;
 
+/// The major device number of the registered block devices and their partitions.
+///
+/// This is the major device number that Linux uses for the extended device numbers of the
+/// block devices (i.e., `blkext`). The minor device numbers are allocated in order.
+pub const BLOCK_MAJOR: u32 = 259;
+
 pub trait BlockDevice: Send + Sync + Any + Debug {
     /// Enqueues a new `SubmittedBio` to the block device.
     fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError>;
 
     /// Returns the metadata of the block device.
     fn metadata(&self) -> BlockDeviceMeta;
+
+    /// Returns the statistics of the timed-out requests of the block device.
+    ///
+    /// Devices that do not detect hung I/O return `None`.
+    fn hung_io_stats(&self) -> Option<&HungIoStats> {
+        None
+    }
+
+    /// Returns the request queue of the block device.
+    ///
+    /// Devices that do not queue the requests in a `BioRequestSingleQueue` return `None`.
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        None
+    }
+
+    /// Returns the maximum number of sectors that a discard bio can cover.
+    ///
+    /// Devices that do not support discarding the sectors return zero.
+    fn max_discard_sectors(&self) -> usize {
+        0
+    }
+
+    /// Returns the maximum number of sectors that a write-zeroes bio can cover.
+    ///
+    /// Devices that do not support writing zeroes without data return zero.
+    fn max_write_zeroes_sectors(&self) -> usize {
+        0
+    }
 }
 
 /// Metadata for a block device.
@@ -74,35 +122,138 @@ impl dyn BlockDevice {
     pub fn downcast_ref<T: BlockDevice>(&self) -> Option<&T> {
         (self as &dyn Any).downcast_ref::<T>()
     }
+
+    /// Plugs the request queue of the block device, if any.
+    ///
+    /// See [`BioRequestSingleQueue::plug`] for details.
+    pub fn plug(&self) -> Option<QueuePlug<'_>> {
+        self.request_queue().map(BioRequestSingleQueue::plug)
+    }
 }
 
+/// Registers a block device with the name.
+///
+/// The partitions of the device are not scanned here, since the driver may not be ready to
+/// serve the requests yet. See [`scan_partitions`] for details.
 pub fn register_device(name: String, device: Arc<dyn BlockDevice>) {
+    let minor = {
+        let mut block_devs = COMPONENT.get().unwrap().block_device_table.lock();
+        let minor = (0..)
+            .find(|minor| block_devs.values().all(|entry| entry.minor != *minor))
+            .unwrap();
+        block_devs.insert(
+            name.clone(),
+            RegisteredDevice {
+                minor,
+                device: device.clone(),
+            },
+        );
+        minor
+    };
+
+    if let Err(err) = sysfs::add_device(&name, minor, &device) {
+        log::warn!(
+            "failed to add block device {} to the device model: {:?}",
+            name,
+            err
+        );
+    }
+}
+
+pub fn get_device(str: &str) -> Option<Arc<dyn BlockDevice>> {
     COMPONENT
         .get()
         .unwrap()
         .block_device_table
         .lock()
-        .insert(name, device);
+        .get(str)
+        .map(|entry| entry.device.clone())
 }
 
-pub fn get_device(str: &str) -> Option<Arc<dyn BlockDevice>> {
+/// Returns the minor device number of the block device, whose major device number is
+/// [`BLOCK_MAJOR`].
+pub fn device_minor(str: &str) -> Option<u32> {
     COMPONENT
         .get()
         .unwrap()
         .block_device_table
         .lock()
         .get(str)
-        .cloned()
+        .map(|entry| entry.minor)
 }
 
 pub fn all_devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
     let block_devs = COMPONENT.get().unwrap().block_device_table.lock();
     block_devs
         .iter()
-        .map(|(name, device)| (name.clone(), device.clone()))
+        .map(|(name, entry)| (name.clone(), entry.device.clone()))
         .collect()
 }
 
+/// Scans the partition table of the block device and registers its partitions.
+///
+/// The old partitions of the device are unregistered first, which fails if any of them is in
+/// use. The partitions are named after the device, with a `p` before the partition number if
+/// the name ends with a digit (e.g., `vda1` and `nvme0n1p1`), as in Linux.
+///
+/// The device must be able to serve the requests when it is scanned.
+pub fn scan_partitions(name: &str) -> Result<(), ScanError> {
+    let device = get_device(name).ok_or(ScanError::NotFound)?;
+    if device.downcast_ref::<Partition>().is_some() {
+        return Err(ScanError::IsPartition);
+    }
+
+    let partitions =
+        partition::read_partition_table(device.as_ref()).map_err(|_| ScanError::IoError)?;
+
+    let old_names: Vec<String> = {
+        let mut block_devs = COMPONENT.get().unwrap().block_device_table.lock();
+        let old_names: Vec<String> = block_devs
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .device
+                    .downcast_ref::<Partition>()
+                    .is_some_and(|partition| Arc::ptr_eq(partition.parent(), &device))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        // The partition is in use if anyone else holds it.
+        if old_names
+            .iter()
+            .any(|name| Arc::strong_count(&block_devs[name].device) > 1)
+        {
+            return Err(ScanError::Busy);
+        }
+        for name in old_names.iter() {
+            block_devs.remove(name);
+        }
+        old_names
+    };
+    for name in old_names {
+        if let Err(err) = sysfs::remove_device(&name) {
+            log::warn!(
+                "failed to remove block device {} from the device model: {:?}",
+                name,
+                err
+            );
+        }
+    }
+
+    let separator = if name.ends_with(|c: char| c.is_ascii_digit()) {
+        "p"
+    } else {
+        ""
+    };
+    for info in partitions {
+        let partition_name = format!("{}{}{}", name, separator, info.number);
+        let partition = Arc::new(Partition::new(device.clone(), info));
+        register_device(partition_name, partition);
+    }
+
+    Ok(())
+}
+
 static COMPONENT: Once<Component> = Once::new();
 
 #[init_component]
@@ -114,7 +265,13 @@ fn component_init() -> Result<(), ComponentInitError> {
 
 #[derive(Debug)]
 struct Component {
-    block_device_table: SpinLock<BTreeMap<String, Arc<dyn BlockDevice>>>,
+    block_device_table: SpinLock<BTreeMap<String, RegisteredDevice>>,
+}
+
+#[derive(Debug)]
+struct RegisteredDevice {
+    minor: u32,
+    device: Arc<dyn BlockDevice>,
 }
 
 impl Component {
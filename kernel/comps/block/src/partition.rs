@@ -0,0 +1,363 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The partitions of the block devices.
+//!
+//! The partition tables in the GUID Partition Table (GPT) format and the Master Boot Record
+//! (MBR) format are supported. For the MBR format, the logical partitions in the extended
+//! partition are numbered from 5, as in Linux.
+//!
+//! Reference:
+//! - <https://uefi.org/specs/UEFI/2.10/05_GUID_Partition_Table_Format.html>
+//! - <https://en.wikipedia.org/wiki/Master_boot_record>
+
+use ostd::mm::VmIo;
+
+use crate::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// A partition of a block device, which forwards the requests to the device.
+#[derive(Debug)]
+pub struct Partition {
+    parent: Arc<dyn BlockDevice>,
+    info: PartitionInfo,
+}
+
+/// The location of a partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PartitionInfo {
+    /// The partition number, which starts from 1.
+    pub(crate) number: usize,
+    /// The first sector of the partition on the device.
+    pub(crate) start: u64,
+    /// The number of the sectors of the partition.
+    pub(crate) nr_sectors: u64,
+}
+
+impl Partition {
+    pub(crate) fn new(parent: Arc<dyn BlockDevice>, info: PartitionInfo) -> Self {
+        Self { parent, info }
+    }
+
+    /// Returns the block device that contains the partition.
+    pub fn parent(&self) -> &Arc<dyn BlockDevice> {
+        &self.parent
+    }
+
+    /// Returns the partition number, which starts from 1.
+    pub fn number(&self) -> usize {
+        self.info.number
+    }
+
+    /// Returns the first sector of the partition on the parent device.
+    pub fn start_sector(&self) -> u64 {
+        self.info.start
+    }
+}
+
+impl BlockDevice for Partition {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.type_() != BioType::Flush && bio.sid_range().end.to_raw() > self.info.nr_sectors {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+
+        bio.remap(self.info.start).submit(self.parent.as_ref())?;
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.parent.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.info.nr_sectors as usize,
+        }
+    }
+
+    fn max_discard_sectors(&self) -> usize {
+        self.parent.max_discard_sectors()
+    }
+
+    fn max_write_zeroes_sectors(&self) -> usize {
+        self.parent.max_write_zeroes_sectors()
+    }
+}
+
+/// The error type returned when scanning the partitions of a block device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanError {
+    /// The block device does not exist
+    NotFound,
+    /// The block device is a partition
+    IsPartition,
+    /// Some partitions of the block device are in use
+    Busy,
+    /// The partition table cannot be read
+    IoError,
+}
+
+/// The partition types of the extended partitions in the MBR format.
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0f, 0x85];
+/// The partition type of the protective MBR of the GPT format.
+const MBR_GPT_PROTECTIVE_TYPE: u8 = 0xee;
+/// The maximum number of the logical partitions, which bounds the walk of the linked list of
+/// the extended boot records in case it has a cycle.
+const MAX_NR_LOGICAL_PARTITIONS: usize = 64;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_ENTRY_SIZE: usize = 128;
+/// The maximum size of the partition entries, which is large enough for the GPT created by any
+/// common tool (usually 128 entries in 16 KiB).
+const MAX_GPT_ENTRIES_SIZE: usize = 1024 * 1024;
+
+/// Reads the partition table of the block device.
+///
+/// Returns an empty vector if the device has no recognized partition table.
+pub(crate) fn read_partition_table(device: &dyn BlockDevice) -> ostd::Result<Vec<PartitionInfo>> {
+    let nr_sectors = device.metadata().nr_sectors as u64;
+    if nr_sectors == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut mbr = [0u8; SECTOR_SIZE];
+    device.read_bytes(0, &mut mbr)?;
+    let Some(entries) = parse_mbr(&mbr) else {
+        return Ok(Vec::new());
+    };
+
+    let partitions = if entries
+        .iter()
+        .any(|entry| entry.type_ == MBR_GPT_PROTECTIVE_TYPE)
+    {
+        read_gpt(device, nr_sectors)?
+    } else {
+        read_mbr_partitions(device, &entries, nr_sectors)?
+    };
+
+    // Like Linux, ignore the partitions that start beyond the device and truncate those that
+    // end beyond it.
+    Ok(partitions
+        .into_iter()
+        .filter(|info| info.start < nr_sectors && info.nr_sectors > 0)
+        .map(|info| PartitionInfo {
+            nr_sectors: info.nr_sectors.min(nr_sectors - info.start),
+            ..info
+        })
+        .collect())
+}
+
+/// An entry in the partition table of an MBR.
+#[derive(Debug, Clone, Copy)]
+struct MbrEntry {
+    status: u8,
+    type_: u8,
+    start: u64,
+    nr_sectors: u64,
+}
+
+impl MbrEntry {
+    fn is_extended(&self) -> bool {
+        MBR_EXTENDED_TYPES.contains(&self.type_)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.type_ == 0 || self.nr_sectors == 0
+    }
+}
+
+/// Parses the partition table of an MBR or an extended boot record.
+///
+/// Returns `None` if the sector is not an MBR. Like Linux, the sector is rejected if the boot
+/// indicators are invalid, which is the case for the boot sectors of the FAT file systems.
+fn parse_mbr(sector: &[u8; SECTOR_SIZE]) -> Option<[MbrEntry; 4]> {
+    if sector[510..] != [0x55, 0xaa] {
+        return None;
+    }
+
+    let entries: [MbrEntry; 4] = core::array::from_fn(|i| {
+        let entry = &sector[446 + i * 16..446 + (i + 1) * 16];
+        MbrEntry {
+            status: entry[0],
+            type_: entry[4],
+            start: u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64,
+            nr_sectors: u32::from_le_bytes(entry[12..16].try_into().unwrap()) as u64,
+        }
+    });
+    if entries
+        .iter()
+        .any(|entry| entry.status != 0 && entry.status != 0x80)
+    {
+        return None;
+    }
+
+    Some(entries)
+}
+
+fn read_mbr_partitions(
+    device: &dyn BlockDevice,
+    entries: &[MbrEntry; 4],
+    nr_sectors: u64,
+) -> ostd::Result<Vec<PartitionInfo>> {
+    let mut partitions = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.is_empty() || entry.is_extended() {
+            continue;
+        }
+        partitions.push(PartitionInfo {
+            number: i + 1,
+            start: entry.start,
+            nr_sectors: entry.nr_sectors,
+        });
+    }
+
+    // The logical partitions are in a linked list of extended boot records (EBRs). The first
+    // entry of each EBR is relative to the EBR, and the second entry (i.e., the next EBR) is
+    // relative to the extended partition.
+    let Some(extended) = entries
+        .iter()
+        .find(|entry| !entry.is_empty() && entry.is_extended())
+    else {
+        return Ok(partitions);
+    };
+    let mut ebr_start = extended.start;
+    let mut number = 5;
+    for _ in 0..MAX_NR_LOGICAL_PARTITIONS {
+        if ebr_start >= nr_sectors {
+            break;
+        }
+
+        let mut ebr = [0u8; SECTOR_SIZE];
+        device.read_bytes(ebr_start as usize * SECTOR_SIZE, &mut ebr)?;
+        let Some([logical, next, ..]) = parse_mbr(&ebr) else {
+            break;
+        };
+
+        if !logical.is_empty() {
+            partitions.push(PartitionInfo {
+                number,
+                start: ebr_start + logical.start,
+                nr_sectors: logical.nr_sectors,
+            });
+            number += 1;
+        }
+
+        if next.is_empty() || !next.is_extended() {
+            break;
+        }
+        ebr_start = extended.start + next.start;
+    }
+
+    Ok(partitions)
+}
+
+/// Reads the partitions in the GPT, using the backup GPT if the primary one is corrupted.
+fn read_gpt(device: &dyn BlockDevice, nr_sectors: u64) -> ostd::Result<Vec<PartitionInfo>> {
+    let last_lba = nr_sectors - 1;
+
+    let header = match read_gpt_header(device, 1, last_lba)? {
+        Some(header) => header,
+        None => match read_gpt_header(device, last_lba, last_lba)? {
+            Some(header) => header,
+            None => return Ok(Vec::new()),
+        },
+    };
+
+    let entries_size = header.nr_entries as usize * GPT_ENTRY_SIZE;
+    let mut entries = vec![0u8; entries_size.next_multiple_of(SECTOR_SIZE)];
+    device.read_bytes(header.entries_lba as usize * SECTOR_SIZE, &mut entries)?;
+    if crc32(&entries[..entries_size]) != header.entries_crc {
+        return Ok(Vec::new());
+    }
+
+    let mut partitions = Vec::new();
+    for (i, entry) in entries[..entries_size]
+        .chunks_exact(GPT_ENTRY_SIZE)
+        .enumerate()
+    {
+        let is_unused = entry[..16].iter().all(|&byte| byte == 0);
+        let first_lba = u64::from_le_bytes(entry[32..40].try_into().unwrap());
+        let end_lba = u64::from_le_bytes(entry[40..48].try_into().unwrap());
+        if is_unused || first_lba > end_lba || end_lba > last_lba {
+            continue;
+        }
+
+        partitions.push(PartitionInfo {
+            number: i + 1,
+            start: first_lba,
+            nr_sectors: end_lba - first_lba + 1,
+        });
+    }
+
+    Ok(partitions)
+}
+
+/// The fields of a GPT header that are used to find the partition entries.
+struct GptHeader {
+    entries_lba: u64,
+    nr_entries: u32,
+    entries_crc: u32,
+}
+
+/// Reads and validates the GPT header at the LBA.
+fn read_gpt_header(
+    device: &dyn BlockDevice,
+    lba: u64,
+    last_lba: u64,
+) -> ostd::Result<Option<GptHeader>> {
+    let mut sector = [0u8; SECTOR_SIZE];
+    device.read_bytes(lba as usize * SECTOR_SIZE, &mut sector)?;
+
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(sector[offset..offset + 4].try_into().unwrap());
+    let read_u64 =
+        |offset: usize| u64::from_le_bytes(sector[offset..offset + 8].try_into().unwrap());
+
+    if &sector[..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+    let header_size = read_u32(12) as usize;
+    if !(92..=SECTOR_SIZE).contains(&header_size) {
+        return Ok(None);
+    }
+    let header_crc = read_u32(16);
+    let mut header = sector;
+    header[16..20].fill(0);
+    if crc32(&header[..header_size]) != header_crc {
+        return Ok(None);
+    }
+
+    let gpt_header = GptHeader {
+        entries_lba: read_u64(72),
+        nr_entries: read_u32(80),
+        entries_crc: read_u32(88),
+    };
+    let entries_size = gpt_header.nr_entries as usize * GPT_ENTRY_SIZE;
+    if read_u64(24) != lba
+        || read_u32(84) as usize != GPT_ENTRY_SIZE
+        || entries_size > MAX_GPT_ENTRIES_SIZE
+        || gpt_header.entries_lba > last_lba
+        || last_lba - gpt_header.entries_lba < (entries_size / SECTOR_SIZE) as u64
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(gpt_header))
+}
+
+/// Computes the CRC-32 checksum that is used by the GPT (i.e., the one used by zlib).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
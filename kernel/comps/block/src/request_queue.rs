@@ -1,29 +1,52 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::sync::{Mutex, WaitQueue};
+use alloc::boxed::Box;
+use core::{sync::atomic::AtomicU64, time::Duration};
+
+use ostd::{
+    sync::{Mutex, WaitQueue},
+    task::Task,
+    timer::Jiffies,
+};
 
 use super::{
     bio::{BioEnqueueError, BioType, SubmittedBio},
     id::Sid,
+    scheduler::{new_scheduler, DeadlineTunables, IoScheduler, SchedulerKind},
 };
 use crate::prelude::*;
 
-/// A simple block I/O request queue backed by one internal FIFO queue.
+/// A block I/O request queue with an I/O scheduler.
 ///
-/// It is a FIFO producer-consumer queue, where the producer (e.g., filesystem)
+/// It is a producer-consumer queue, where the producer (e.g., filesystem)
 /// submits requests to the queue, and the consumer (e.g., block device driver)
 /// continuously consumes and processes these requests from the queue.
 ///
-/// It supports merging the new request with the front request if if the type
-/// is same and the sector range is contiguous.
+/// A new `SubmittedBio` is merged into a queued request if the type is same and
+/// the sector range is contiguous. The I/O scheduler of the queue decides which
+/// requests a bio can be merged into and in which order the requests are dispatched.
+///
+/// The queue can be plugged with [`BioRequestSingleQueue::plug`] to hold back the
+/// requests while a batch of bios is being submitted, so that the bios have a better
+/// chance to be merged and sorted.
 pub struct BioRequestSingleQueue {
-    queue: Mutex<VecDeque<BioRequest>>,
+    scheduler: Mutex<Box<dyn IoScheduler>>,
     num_requests: AtomicUsize,
     wait_queue: WaitQueue,
     max_nr_segments_per_bio: usize,
+    deadline_tunables: Arc<DeadlineTunables>,
+    nr_plugs: AtomicUsize,
+    /// The time in jiffies when the queue was plugged.
+    plugged_at: AtomicU64,
+    stats: QueueStats,
 }
 
 impl BioRequestSingleQueue {
+    /// The maximum number of requests held back by a plugged queue.
+    const MAX_PLUGGED_REQUESTS: usize = 32;
+    /// The maximum time that a plugged queue holds back the requests.
+    const PLUG_TIMEOUT: Duration = Duration::from_millis(3);
+
     /// Creates an empty queue.
     pub fn new() -> Self {
         Self::with_max_nr_segments_per_bio(usize::MAX)
@@ -31,11 +54,16 @@ impl BioRequestSingleQueue {
 
     /// Creates an empty queue with the upper bound for the number of segments in a bio.
     pub fn with_max_nr_segments_per_bio(max_nr_segments_per_bio: usize) -> Self {
+        let deadline_tunables = Arc::new(DeadlineTunables::new());
         Self {
-            queue: Mutex::new(VecDeque::new()),
+            scheduler: Mutex::new(new_scheduler(SchedulerKind::Deadline, &deadline_tunables)),
             num_requests: AtomicUsize::new(0),
             wait_queue: WaitQueue::new(),
             max_nr_segments_per_bio,
+            deadline_tunables,
+            nr_plugs: AtomicUsize::new(0),
+            plugged_at: AtomicU64::new(0),
+            stats: QueueStats::new(),
         }
     }
 
@@ -49,9 +77,40 @@ impl BioRequestSingleQueue {
         self.num_requests.load(Ordering::Relaxed)
     }
 
+    /// Returns the kind of the I/O scheduler.
+    pub fn scheduler(&self) -> SchedulerKind {
+        self.scheduler.lock().kind()
+    }
+
+    /// Switches to the I/O scheduler of the given kind.
+    ///
+    /// The queued requests are moved to the new scheduler.
+    pub fn set_scheduler(&self, kind: SchedulerKind) {
+        let mut scheduler = self.scheduler.lock();
+        if scheduler.kind() == kind {
+            return;
+        }
+
+        let requests = scheduler.drain();
+        *scheduler = new_scheduler(kind, &self.deadline_tunables);
+        for request in requests {
+            scheduler.add_request(request);
+        }
+    }
+
+    /// Returns the tunables of the `mq-deadline` scheduler.
+    pub fn deadline_tunables(&self) -> &DeadlineTunables {
+        &self.deadline_tunables
+    }
+
+    /// Returns the statistics of this queue.
+    pub fn stats(&self) -> &QueueStats {
+        &self.stats
+    }
+
     /// Enqueues a `SubmittedBio` to this queue.
     ///
-    /// When enqueueing the `SubmittedBio`, try to insert it into the last request if the
+    /// When enqueueing the `SubmittedBio`, try to merge it into a queued request if the
     /// type is same and the sector range is contiguous.
     /// Otherwise, creates and inserts a new request for the `SubmittedBio`.
     ///
@@ -61,20 +120,21 @@ impl BioRequestSingleQueue {
             return Err(BioEnqueueError::TooBig);
         }
 
-        let mut queue = self.queue.lock();
-        if let Some(request) = queue.front_mut() {
-            if request.can_merge(&bio)
-                && request.num_segments() + bio.segments().len() <= self.max_nr_segments_per_bio
-            {
-                request.merge_bio(bio);
-                return Ok(());
-            }
-        }
+        let type_ = bio.type_();
+        let mut scheduler = self.scheduler.lock();
+        let Err(bio) = scheduler.merge_bio(bio, self.max_nr_segments_per_bio) else {
+            // Merging a bio may also merge two requests.
+            self.num_requests
+                .store(scheduler.num_requests(), Ordering::Relaxed);
+            drop(scheduler);
+            self.stats.inc_merges(type_);
+            return Ok(());
+        };
 
-        let new_request = BioRequest::from(bio);
-        queue.push_front(new_request);
-        self.inc_num_requests();
-        drop(queue);
+        scheduler.add_request(BioRequest::from(bio));
+        self.num_requests
+            .store(scheduler.num_requests(), Ordering::Relaxed);
+        drop(scheduler);
 
         self.wait_queue.wake_all();
         Ok(())
@@ -84,21 +144,27 @@ impl BioRequestSingleQueue {
     ///
     /// This method will wait until one request can be retrieved.
     pub fn dequeue(&self) -> BioRequest {
-        let mut num_requests = self.num_requests();
-
         loop {
-            if num_requests > 0 {
-                let mut queue = self.queue.lock();
-                if let Some(request) = queue.pop_back() {
-                    self.dec_num_requests();
+            if self.num_requests() > 0 {
+                if self.is_plugged() {
+                    // Polls the plug, which may time out without a wakeup.
+                    Task::yield_now();
+                    continue;
+                }
+
+                let mut scheduler = self.scheduler.lock();
+                if let Some(request) = scheduler.dispatch_request() {
+                    self.num_requests
+                        .store(scheduler.num_requests(), Ordering::Relaxed);
+                    drop(scheduler);
+                    self.stats.add_request(&request);
                     return request;
                 }
             }
 
-            num_requests = self.wait_queue.wait_until(|| {
-                let num_requests = self.num_requests();
-                if num_requests > 0 {
-                    Some(num_requests)
+            self.wait_queue.wait_until(|| {
+                if self.num_requests() > 0 {
+                    Some(())
                 } else {
                     None
                 }
@@ -106,12 +172,36 @@ impl BioRequestSingleQueue {
         }
     }
 
-    fn dec_num_requests(&self) {
-        self.num_requests.fetch_sub(1, Ordering::Relaxed);
+    /// Plugs this queue until the returned `QueuePlug` is dropped.
+    ///
+    /// While the queue is plugged, the requests are held back from the consumer,
+    /// unless too many requests have been queued or the plug has lasted too long.
+    /// The plug should be dropped once the batch of bios has been submitted,
+    /// before waiting for their completion.
+    pub fn plug(&self) -> QueuePlug<'_> {
+        if self.nr_plugs.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.plugged_at
+                .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+        }
+        QueuePlug { queue: self }
+    }
+
+    fn unplug(&self) {
+        if self.nr_plugs.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.wait_queue.wake_all();
+        }
     }
 
-    fn inc_num_requests(&self) {
-        self.num_requests.fetch_add(1, Ordering::Relaxed);
+    fn is_plugged(&self) -> bool {
+        if self.nr_plugs.load(Ordering::Relaxed) == 0
+            || self.num_requests() >= Self::MAX_PLUGGED_REQUESTS
+        {
+            return false;
+        }
+
+        let now = Jiffies::elapsed().as_u64();
+        let plugged_at = self.plugged_at.load(Ordering::Relaxed);
+        Jiffies::new(now.saturating_sub(plugged_at)).as_duration() < Self::PLUG_TIMEOUT
     }
 }
 
@@ -125,11 +215,73 @@ impl Debug for BioRequestSingleQueue {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("BioRequestSingleQueue")
             .field("num_requests", &self.num_requests())
-            .field("queue", &self.queue.lock())
+            .field("scheduler", &self.scheduler())
+            .field("stats", &self.stats)
             .finish()
     }
 }
 
+/// A plug of a `BioRequestSingleQueue`.
+///
+/// The queue is unplugged when the last plug is dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct QueuePlug<'a> {
+    queue: &'a BioRequestSingleQueue,
+}
+
+impl Drop for QueuePlug<'_> {
+    fn drop(&mut self) {
+        self.queue.unplug();
+    }
+}
+
+/// The statistics of a `BioRequestSingleQueue`.
+///
+/// The requests are accounted when they are dispatched to the driver,
+/// while the merges are accounted when the bios are merged into the queued requests.
+#[derive(Debug)]
+pub struct QueueStats {
+    nr_requests: [AtomicUsize; 5],
+    nr_merges: [AtomicUsize; 5],
+    nr_sectors: [AtomicUsize; 5],
+}
+
+impl QueueStats {
+    const fn new() -> Self {
+        Self {
+            nr_requests: [const { AtomicUsize::new(0) }; 5],
+            nr_merges: [const { AtomicUsize::new(0) }; 5],
+            nr_sectors: [const { AtomicUsize::new(0) }; 5],
+        }
+    }
+
+    /// Returns the number of dispatched requests of the type.
+    pub fn nr_requests(&self, type_: BioType) -> usize {
+        self.nr_requests[type_ as usize].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bios of the type that have been merged into the queued requests.
+    pub fn nr_merges(&self, type_: BioType) -> usize {
+        self.nr_merges[type_ as usize].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of sectors of the dispatched requests of the type.
+    pub fn nr_sectors(&self, type_: BioType) -> usize {
+        self.nr_sectors[type_ as usize].load(Ordering::Relaxed)
+    }
+
+    fn inc_merges(&self, type_: BioType) {
+        self.nr_merges[type_ as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_request(&self, request: &BioRequest) {
+        let type_ = request.type_() as usize;
+        self.nr_requests[type_].fetch_add(1, Ordering::Relaxed);
+        self.nr_sectors[type_].fetch_add(request.num_sectors(), Ordering::Relaxed);
+    }
+}
+
 /// The block I/O request.
 ///
 /// The advantage of this data structure is to merge several `SubmittedBio`s that are
@@ -180,6 +332,11 @@ impl BioRequest {
         if rq_bio.type_() != self.type_ {
             return false;
         }
+        // The bios without data are not merged, so that the requests do not exceed the
+        // limits of the device (e.g., `BlockDevice::max_discard_sectors`).
+        if !self.type_.has_data() {
+            return false;
+        }
 
         rq_bio.sid_range().start == self.sid_range.end
             || rq_bio.sid_range().end == self.sid_range.start
@@ -207,6 +364,19 @@ impl BioRequest {
 
         self.num_segments += rq_bio_nr_segments;
     }
+
+    /// Merges another request that starts at the end of this request into this request.
+    ///
+    /// # Panics
+    ///
+    /// If the type differs or the sector ranges are not contiguous, this method will panic.
+    pub fn merge_request(&mut self, mut other: BioRequest) {
+        assert!(other.type_ == self.type_ && other.sid_range.start == self.sid_range.end);
+
+        self.sid_range.end = other.sid_range.end;
+        self.num_segments += other.num_segments;
+        self.bios.append(&mut other.bios);
+    }
 }
 
 impl From<SubmittedBio> for BioRequest {
@@ -0,0 +1,530 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I/O schedulers of the block request queues.
+//!
+//! An I/O scheduler holds the requests of a queue until the driver dispatches them,
+//! deciding which of the queued requests is dispatched next.
+//! Two schedulers are provided:
+//! - `none`, which dispatches the requests in the order of their arrival;
+//! - `mq-deadline`, which sorts the requests by their sectors to reduce seeking,
+//!   while guaranteeing that no request waits longer than its deadline.
+
+use alloc::boxed::Box;
+use core::sync::atomic::AtomicU64;
+
+use ostd::{arch::timer::TIMER_FREQ, timer::Jiffies};
+
+use crate::{
+    bio::{BioType, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    request_queue::BioRequest,
+};
+
+/// The kinds of the I/O schedulers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerKind {
+    /// Dispatches the requests in the order of their arrival.
+    None,
+    /// Dispatches the requests in the order of their sectors,
+    /// unless the deadline of a request expires.
+    Deadline,
+}
+
+impl SchedulerKind {
+    /// All the kinds of the I/O schedulers.
+    pub const ALL: [SchedulerKind; 2] = [SchedulerKind::Deadline, SchedulerKind::None];
+
+    /// Returns the name of the scheduler, as shown in sysfs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Deadline => "mq-deadline",
+        }
+    }
+}
+
+impl TryFrom<&str> for SchedulerKind {
+    type Error = ();
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or(())
+    }
+}
+
+/// An I/O scheduler.
+pub(crate) trait IoScheduler: Send + Sync {
+    /// Returns the kind of the scheduler.
+    fn kind(&self) -> SchedulerKind;
+
+    /// Tries to merge the `SubmittedBio` into one of the queued requests.
+    ///
+    /// The `SubmittedBio` is returned if it cannot be merged.
+    fn merge_bio(&mut self, bio: SubmittedBio, max_nr_segments: usize) -> Result<(), SubmittedBio>;
+
+    /// Adds a new request to the scheduler.
+    fn add_request(&mut self, request: BioRequest);
+
+    /// Removes the request that should be dispatched next.
+    fn dispatch_request(&mut self) -> Option<BioRequest>;
+
+    /// Returns the number of the queued requests.
+    fn num_requests(&self) -> usize;
+
+    /// Removes all the queued requests in the order that they would be dispatched.
+    fn drain(&mut self) -> Vec<BioRequest>;
+}
+
+/// Creates an I/O scheduler of the given kind.
+pub(crate) fn new_scheduler(
+    kind: SchedulerKind,
+    tunables: &Arc<DeadlineTunables>,
+) -> Box<dyn IoScheduler> {
+    match kind {
+        SchedulerKind::None => Box::new(NoopScheduler::new()),
+        SchedulerKind::Deadline => Box::new(DeadlineScheduler::new(tunables.clone())),
+    }
+}
+
+/// The `none` scheduler.
+///
+/// A new bio can only be merged into the most recently queued request.
+struct NoopScheduler {
+    queue: VecDeque<BioRequest>,
+}
+
+impl NoopScheduler {
+    fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl IoScheduler for NoopScheduler {
+    fn kind(&self) -> SchedulerKind {
+        SchedulerKind::None
+    }
+
+    fn merge_bio(&mut self, bio: SubmittedBio, max_nr_segments: usize) -> Result<(), SubmittedBio> {
+        if let Some(request) = self.queue.back_mut() {
+            if request.can_merge(&bio)
+                && request.num_segments() + bio.segments().len() <= max_nr_segments
+            {
+                request.merge_bio(bio);
+                return Ok(());
+            }
+        }
+        Err(bio)
+    }
+
+    fn add_request(&mut self, request: BioRequest) {
+        self.queue.push_back(request);
+    }
+
+    fn dispatch_request(&mut self) -> Option<BioRequest> {
+        self.queue.pop_front()
+    }
+
+    fn num_requests(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn drain(&mut self) -> Vec<BioRequest> {
+        self.queue.drain(..).collect()
+    }
+}
+
+/// The tunables of the `mq-deadline` scheduler.
+#[derive(Debug)]
+pub struct DeadlineTunables {
+    read_expire_ms: AtomicU64,
+    write_expire_ms: AtomicU64,
+    fifo_batch: AtomicUsize,
+    writes_starved: AtomicUsize,
+}
+
+impl DeadlineTunables {
+    /// Creates the tunables with the default values of Linux.
+    pub const fn new() -> Self {
+        Self {
+            read_expire_ms: AtomicU64::new(500),
+            write_expire_ms: AtomicU64::new(5000),
+            fifo_batch: AtomicUsize::new(16),
+            writes_starved: AtomicUsize::new(2),
+        }
+    }
+
+    /// Returns the deadline of the read requests in milliseconds.
+    pub fn read_expire_ms(&self) -> u64 {
+        self.read_expire_ms.load(Ordering::Relaxed)
+    }
+
+    /// Sets the deadline of the read requests in milliseconds.
+    pub fn set_read_expire_ms(&self, ms: u64) {
+        self.read_expire_ms.store(ms, Ordering::Relaxed);
+    }
+
+    /// Returns the deadline of the write requests in milliseconds.
+    pub fn write_expire_ms(&self) -> u64 {
+        self.write_expire_ms.load(Ordering::Relaxed)
+    }
+
+    /// Sets the deadline of the write requests in milliseconds.
+    pub fn set_write_expire_ms(&self, ms: u64) {
+        self.write_expire_ms.store(ms, Ordering::Relaxed);
+    }
+
+    /// Returns the maximum number of requests that are dispatched in a batch
+    /// in the order of their sectors.
+    pub fn fifo_batch(&self) -> usize {
+        self.fifo_batch.load(Ordering::Relaxed)
+    }
+
+    /// Sets the maximum number of requests in a batch.
+    ///
+    /// A zero value is treated as one.
+    pub fn set_fifo_batch(&self, fifo_batch: usize) {
+        self.fifo_batch.store(fifo_batch.max(1), Ordering::Relaxed);
+    }
+
+    /// Returns the number of times that the reads can be preferred over the pending writes.
+    pub fn writes_starved(&self) -> usize {
+        self.writes_starved.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of times that the reads can be preferred over the pending writes.
+    pub fn set_writes_starved(&self, writes_starved: usize) {
+        self.writes_starved.store(writes_starved, Ordering::Relaxed);
+    }
+}
+
+impl Default for DeadlineTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The data direction of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Read = 0,
+    Write = 1,
+}
+
+impl Direction {
+    fn of(type_: BioType) -> Self {
+        match type_ {
+            BioType::Read => Self::Read,
+            BioType::Write | BioType::Discard | BioType::WriteZeroes | BioType::Flush => {
+                Self::Write
+            }
+        }
+    }
+}
+
+/// A request held by the `mq-deadline` scheduler.
+struct DeadlineRequest {
+    request: BioRequest,
+    /// The deadline in jiffies.
+    deadline: u64,
+}
+
+/// The requests of one direction held by the `mq-deadline` scheduler.
+#[derive(Default)]
+struct DirectionQueue {
+    /// The requests sorted by their starting sectors.
+    ///
+    /// Each request is also keyed by its arrival sequence number,
+    /// so that the requests starting at the same sector can coexist.
+    sorted: BTreeMap<(Sid, u64), DeadlineRequest>,
+    /// The starting sectors of the requests in the order of their arrival.
+    fifo: BTreeMap<u64, Sid>,
+    /// The sector after the last dispatched request.
+    next_sid: Option<Sid>,
+}
+
+impl DirectionQueue {
+    fn insert(&mut self, seq: u64, request: DeadlineRequest) {
+        let start = request.request.sid_range().start;
+        self.sorted.insert((start, seq), request);
+        self.fifo.insert(seq, start);
+    }
+
+    fn remove(&mut self, key: (Sid, u64)) -> BioRequest {
+        self.fifo.remove(&key.1);
+        self.sorted.remove(&key).unwrap().request
+    }
+
+    /// Returns the key of the oldest request if its deadline has expired.
+    fn expired_key(&self, now: u64) -> Option<(Sid, u64)> {
+        let (&seq, &start) = self.fifo.first_key_value()?;
+        let key = (start, seq);
+        (self.sorted[&key].deadline <= now).then_some(key)
+    }
+
+    /// Returns the key of the oldest request.
+    fn oldest_key(&self) -> Option<(Sid, u64)> {
+        let (&seq, &start) = self.fifo.first_key_value()?;
+        Some((start, seq))
+    }
+
+    /// Returns the key of the first request starting at or after `sid`.
+    fn key_from(&self, sid: Sid) -> Option<(Sid, u64)> {
+        self.sorted.range((sid, 0)..).next().map(|(key, _)| *key)
+    }
+
+    /// Tries to merge the `SubmittedBio` into the request that ends at its start (a back merge)
+    /// or that starts at its end (a front merge).
+    fn merge_bio(&mut self, bio: SubmittedBio, max_nr_segments: usize) -> Result<(), SubmittedBio> {
+        let fits = |request: &BioRequest, nr_segments: usize| {
+            request.num_segments() + nr_segments <= max_nr_segments
+        };
+        let bio_range = bio.sid_range().clone();
+
+        let back_key = self
+            .sorted
+            .range(..(bio_range.start, 0))
+            .next_back()
+            .filter(|(_, queued)| {
+                queued.request.sid_range().end == bio_range.start
+                    && queued.request.can_merge(&bio)
+                    && fits(&queued.request, bio.segments().len())
+            })
+            .map(|(key, _)| *key);
+        if let Some(back_key) = back_key {
+            self.sorted
+                .get_mut(&back_key)
+                .unwrap()
+                .request
+                .merge_bio(bio);
+            // The merged request may now be contiguous with the next request.
+            self.merge_next_request(back_key, max_nr_segments);
+            return Ok(());
+        }
+
+        let front_key = self
+            .sorted
+            .range((bio_range.end, 0)..)
+            .next()
+            .filter(|(key, queued)| {
+                key.0 == bio_range.end
+                    && queued.request.can_merge(&bio)
+                    && fits(&queued.request, bio.segments().len())
+            })
+            .map(|(key, _)| *key);
+        if let Some(front_key) = front_key {
+            let mut queued = self.sorted.remove(&front_key).unwrap();
+            queued.request.merge_bio(bio);
+            let seq = front_key.1;
+            self.fifo.insert(seq, bio_range.start);
+            self.sorted.insert((bio_range.start, seq), queued);
+            return Ok(());
+        }
+
+        Err(bio)
+    }
+
+    /// Merges the request after the one with `key` into it if they are contiguous.
+    fn merge_next_request(&mut self, key: (Sid, u64), max_nr_segments: usize) {
+        let queued = &self.sorted[&key];
+        let end = queued.request.sid_range().end;
+        let Some(next_key) = self
+            .sorted
+            .range((end, 0)..)
+            .next()
+            .filter(|(next_key, next)| {
+                next_key.0 == end
+                    && next.request.type_() == queued.request.type_()
+                    && next.request.type_().has_data()
+                    && queued.request.num_segments() + next.request.num_segments()
+                        <= max_nr_segments
+            })
+            .map(|(next_key, _)| *next_key)
+        else {
+            return;
+        };
+
+        self.fifo.remove(&next_key.1);
+        let next = self.sorted.remove(&next_key).unwrap();
+        let queued = self.sorted.get_mut(&key).unwrap();
+        queued.deadline = queued.deadline.min(next.deadline);
+        queued.request.merge_request(next.request);
+    }
+
+    fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+}
+
+/// The `mq-deadline` scheduler.
+///
+/// The reads and the writes are queued separately. Each time the scheduler picks a direction,
+/// preferring the reads unless the writes have been starved for `writes_starved` times,
+/// and dispatches a batch of up to `fifo_batch` requests of the direction in the order of
+/// their sectors. A batch starts from the oldest request if its deadline has expired,
+/// or otherwise continues from where the last batch of the direction stopped.
+///
+/// A flush request is a barrier: all the requests queued before it are dispatched
+/// before it, and the requests queued after it are dispatched after it.
+struct DeadlineScheduler {
+    tunables: Arc<DeadlineTunables>,
+    directions: [DirectionQueue; 2],
+    /// The requests that must be dispatched before the other requests.
+    dispatch: VecDeque<BioRequest>,
+    /// The direction of the current batch.
+    batch_direction: Direction,
+    /// The number of requests dispatched in the current batch.
+    batch_count: usize,
+    /// The number of times that the reads have been preferred over the pending writes.
+    starved: usize,
+    next_seq: u64,
+}
+
+impl DeadlineScheduler {
+    fn new(tunables: Arc<DeadlineTunables>) -> Self {
+        Self {
+            tunables,
+            directions: Default::default(),
+            dispatch: VecDeque::new(),
+            batch_direction: Direction::Read,
+            batch_count: 0,
+            starved: 0,
+            next_seq: 0,
+        }
+    }
+
+    fn expire_jiffies(&self, direction: Direction) -> u64 {
+        let ms = match direction {
+            Direction::Read => self.tunables.read_expire_ms(),
+            Direction::Write => self.tunables.write_expire_ms(),
+        };
+        ms * TIMER_FREQ / 1000
+    }
+
+    /// Moves all the queued requests to the dispatch list, in the order of their sectors.
+    fn drain_to_dispatch(&mut self) {
+        for direction in self.directions.iter_mut() {
+            let sorted = core::mem::take(&mut direction.sorted);
+            direction.fifo.clear();
+            self.dispatch
+                .extend(sorted.into_values().map(|queued| queued.request));
+        }
+    }
+
+    fn choose_direction(&mut self) -> Option<Direction> {
+        let has_reads = !self.directions[Direction::Read as usize].is_empty();
+        let has_writes = !self.directions[Direction::Write as usize].is_empty();
+
+        match (has_reads, has_writes) {
+            (false, false) => None,
+            (true, false) => Some(Direction::Read),
+            (false, true) => {
+                self.starved = 0;
+                Some(Direction::Write)
+            }
+            (true, true) => {
+                if self.starved >= self.tunables.writes_starved() {
+                    self.starved = 0;
+                    Some(Direction::Write)
+                } else {
+                    self.starved += 1;
+                    Some(Direction::Read)
+                }
+            }
+        }
+    }
+
+    fn dispatch_from(&mut self, direction: Direction, key: (Sid, u64)) -> BioRequest {
+        let queue = &mut self.directions[direction as usize];
+        let request = queue.remove(key);
+        queue.next_sid = Some(request.sid_range().end);
+        request
+    }
+}
+
+impl IoScheduler for DeadlineScheduler {
+    fn kind(&self) -> SchedulerKind {
+        SchedulerKind::Deadline
+    }
+
+    fn merge_bio(&mut self, bio: SubmittedBio, max_nr_segments: usize) -> Result<(), SubmittedBio> {
+        if bio.type_() == BioType::Flush {
+            return Err(bio);
+        }
+
+        let direction = Direction::of(bio.type_());
+        self.directions[direction as usize].merge_bio(bio, max_nr_segments)
+    }
+
+    fn add_request(&mut self, request: BioRequest) {
+        if request.type_() == BioType::Flush {
+            self.drain_to_dispatch();
+            self.dispatch.push_back(request);
+            return;
+        }
+
+        let direction = Direction::of(request.type_());
+        let deadline = Jiffies::elapsed().as_u64() + self.expire_jiffies(direction);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.directions[direction as usize].insert(seq, DeadlineRequest { request, deadline });
+    }
+
+    fn dispatch_request(&mut self) -> Option<BioRequest> {
+        if let Some(request) = self.dispatch.pop_front() {
+            return Some(request);
+        }
+
+        // Continues the current batch in the order of the sectors.
+        if self.batch_count < self.tunables.fifo_batch() {
+            let direction = self.batch_direction;
+            let queue = &self.directions[direction as usize];
+            if let Some(key) = queue.next_sid.and_then(|sid| queue.key_from(sid)) {
+                self.batch_count += 1;
+                return Some(self.dispatch_from(direction, key));
+            }
+        }
+
+        // Starts a new batch.
+        let direction = self.choose_direction()?;
+        let queue = &self.directions[direction as usize];
+        let now = Jiffies::elapsed().as_u64();
+        let key = queue
+            .expired_key(now)
+            .or_else(|| {
+                if direction == self.batch_direction {
+                    queue.next_sid.and_then(|sid| queue.key_from(sid))
+                } else {
+                    None
+                }
+            })
+            .or_else(|| queue.oldest_key())
+            .unwrap();
+
+        self.batch_direction = direction;
+        self.batch_count = 1;
+        Some(self.dispatch_from(direction, key))
+    }
+
+    fn num_requests(&self) -> usize {
+        self.dispatch.len()
+            + self
+                .directions
+                .iter()
+                .map(DirectionQueue::len)
+                .sum::<usize>()
+    }
+
+    fn drain(&mut self) -> Vec<BioRequest> {
+        self.drain_to_dispatch();
+        self.dispatch.drain(..).collect()
+    }
+}
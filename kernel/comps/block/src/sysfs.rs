@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Exposes the block devices and their request queues in the device model.
+//!
+//! Each block device gets a directory at `devices/virtual/block/<name>`,
+//! which is linked from `class/block`. Like Linux, the directory contains
+//! the `dev`, `size` and `stat` attributes of the device and the `queue` directory
+//! with the attributes of the request queue. The directory of a partition
+//! contains the `partition` and `start` attributes instead of the `queue` directory.
+
+use alloc::{borrow::Cow, format, string::ToString};
+use core::str::FromStr;
+
+use aster_systree::{device_model, Error, KObject, KObjectBuilder, Result};
+
+use crate::{
+    bio::BioType,
+    partition::Partition,
+    prelude::*,
+    scheduler::{DeadlineTunables, SchedulerKind},
+    BlockDevice, BLOCK_MAJOR, SECTOR_SIZE,
+};
+
+/// The name of the class of the block devices.
+const BLOCK_CLASS_NAME: &str = "block";
+
+/// Adds a block device to the device model.
+pub(crate) fn add_device(name: &str, minor: u32, device: &Arc<dyn BlockDevice>) -> Result<()> {
+    let model = device_model();
+    model.register_class(BLOCK_CLASS_NAME)?;
+    let parent = model.virtual_class_dir(BLOCK_CLASS_NAME)?;
+
+    let mut builder = KObjectBuilder::new(Cow::Owned(name.to_string()));
+    builder.attr("dev", format!("{}:{}", BLOCK_MAJOR, minor));
+    {
+        let device = device.clone();
+        builder.attr_with("size", move || device.metadata().nr_sectors.to_string());
+    }
+    if device.request_queue().is_some() {
+        let device = device.clone();
+        builder.attr_with("stat", move || show_stat(device.as_ref()));
+    }
+    let devtype = if let Some(partition) = device.downcast_ref::<Partition>() {
+        builder
+            .attr("partition", partition.number().to_string())
+            .attr("start", partition.start_sector().to_string());
+        "partition"
+    } else {
+        "disk"
+    };
+    builder.uevent(vec![
+        ("MAJOR", BLOCK_MAJOR.to_string()),
+        ("MINOR", minor.to_string()),
+        ("DEVNAME", name.to_string()),
+        ("DEVTYPE", devtype.to_string()),
+    ]);
+    let kobj = builder.build(parent.as_ref())?;
+
+    if device.request_queue().is_some() {
+        add_queue_dir(&kobj, device)?;
+    }
+
+    model.add_device(&parent, kobj, None, Some(BLOCK_CLASS_NAME))
+}
+
+/// Removes a block device from the device model.
+pub(crate) fn remove_device(name: &str) -> Result<()> {
+    let model = device_model();
+    let parent = model.virtual_class_dir(BLOCK_CLASS_NAME)?;
+    model.remove_device(&parent, name)?;
+    Ok(())
+}
+
+/// Shows the statistics of the device in the format of Linux's `stat` attribute.
+///
+/// The time spent on the requests is not accounted, so the time fields are always zero.
+fn show_stat(device: &dyn BlockDevice) -> String {
+    let queue = device.request_queue().unwrap();
+    let stats = queue.stats();
+    let io_fields = |type_: BioType| {
+        format!(
+            "{:8} {:8} {:8} {:8}",
+            stats.nr_requests(type_),
+            stats.nr_merges(type_),
+            stats.nr_sectors(type_),
+            0
+        )
+    };
+
+    format!(
+        "{} {} {:8} {:8} {:8} {} {:8} {:8}",
+        io_fields(BioType::Read),
+        io_fields(BioType::Write),
+        queue.num_requests(),
+        0,
+        0,
+        io_fields(BioType::Discard),
+        stats.nr_requests(BioType::Flush),
+        0
+    )
+}
+
+/// Adds the `queue` directory, which contains the attributes of the request queue.
+///
+/// The tunables of the `mq-deadline` scheduler are in the `queue/iosched` directory.
+/// They are kept even if another scheduler is selected.
+fn add_queue_dir(kobj: &KObject, device: &Arc<dyn BlockDevice>) -> Result<()> {
+    let mut builder = KObjectBuilder::new(Cow::Borrowed("queue"));
+    {
+        let (show_device, store_device) = (device.clone(), device.clone());
+        builder.attr_rw(
+            "scheduler",
+            move || {
+                let current = show_device.request_queue().unwrap().scheduler();
+                let names: Vec<String> = SchedulerKind::ALL
+                    .iter()
+                    .map(|kind| {
+                        if *kind == current {
+                            format!("[{}]", kind.name())
+                        } else {
+                            kind.name().to_string()
+                        }
+                    })
+                    .collect();
+                names.join(" ")
+            },
+            move |value| {
+                let kind =
+                    SchedulerKind::try_from(value.trim()).map_err(|_| Error::AttributeError)?;
+                store_device.request_queue().unwrap().set_scheduler(kind);
+                Ok(())
+            },
+        );
+    }
+    {
+        let device = device.clone();
+        builder.attr_with("max_segments", move || {
+            device
+                .request_queue()
+                .unwrap()
+                .max_nr_segments_per_bio()
+                .to_string()
+        });
+    }
+    {
+        let device = device.clone();
+        builder.attr_with("discard_max_bytes", move || {
+            (device.max_discard_sectors() as u64)
+                .saturating_mul(SECTOR_SIZE as u64)
+                .to_string()
+        });
+    }
+    {
+        let device = device.clone();
+        builder.attr_with("write_zeroes_max_bytes", move || {
+            (device.max_write_zeroes_sectors() as u64)
+                .saturating_mul(SECTOR_SIZE as u64)
+                .to_string()
+        });
+    }
+    let queue_kobj = builder.build(kobj)?;
+
+    let mut builder = KObjectBuilder::new(Cow::Borrowed("iosched"));
+    add_tunable(
+        &mut builder,
+        device,
+        "read_expire",
+        DeadlineTunables::read_expire_ms,
+        DeadlineTunables::set_read_expire_ms,
+    );
+    add_tunable(
+        &mut builder,
+        device,
+        "write_expire",
+        DeadlineTunables::write_expire_ms,
+        DeadlineTunables::set_write_expire_ms,
+    );
+    add_tunable(
+        &mut builder,
+        device,
+        "fifo_batch",
+        DeadlineTunables::fifo_batch,
+        DeadlineTunables::set_fifo_batch,
+    );
+    add_tunable(
+        &mut builder,
+        device,
+        "writes_starved",
+        DeadlineTunables::writes_starved,
+        DeadlineTunables::set_writes_starved,
+    );
+    queue_kobj.add_child(builder.build(queue_kobj.as_ref())?)?;
+
+    kobj.add_child(queue_kobj)
+}
+
+/// Adds a writable attribute of an integer tunable of the `mq-deadline` scheduler.
+fn add_tunable<T>(
+    builder: &mut KObjectBuilder,
+    device: &Arc<dyn BlockDevice>,
+    name: &'static str,
+    get: fn(&DeadlineTunables) -> T,
+    set: fn(&DeadlineTunables, T),
+) where
+    T: FromStr + ToString + 'static,
+{
+    let (show_device, store_device) = (device.clone(), device.clone());
+    builder.attr_rw(
+        name,
+        move || get(show_device.request_queue().unwrap().deadline_tunables()).to_string(),
+        move |value| {
+            let value = value.trim().parse().map_err(|_| Error::AttributeError)?;
+            set(
+                store_device.request_queue().unwrap().deadline_tunables(),
+                value,
+            );
+            Ok(())
+        },
+    );
+}
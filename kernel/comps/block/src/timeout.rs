@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Deadlines of block I/O requests and hung-I/O detection.
+//!
+//! A driver starts a [`RequestDeadline`] when it hands a request to the hardware.
+//! The driver registers a timeout handler with [`register_timeout_handler`],
+//! which periodically scans the in-flight requests. For each request that has
+//! passed its deadline, the driver decides how to recover (e.g., aborting or
+//! resetting the controller, or requeuing the request) and reports the outcome
+//! to the [`HungIoStats`] of the device.
+
+use core::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    timer::{self, Jiffies},
+};
+
+use crate::bio::BioType;
+
+/// The default deadline of a block request.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The interval between two scans of the in-flight requests.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static REQUEST_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_REQUEST_TIMEOUT.as_millis() as u64);
+
+/// Returns the deadline applied to newly submitted block requests.
+pub fn request_timeout() -> Duration {
+    Duration::from_millis(REQUEST_TIMEOUT_MS.load(Ordering::Relaxed))
+}
+
+/// Sets the deadline applied to newly submitted block requests.
+///
+/// A zero timeout disables the hung-I/O detection.
+pub fn set_request_timeout(timeout: Duration) {
+    REQUEST_TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Registers a handler that checks the in-flight requests of a driver for timeouts.
+///
+/// The handler is called roughly once every second in the timer interrupt context.
+pub fn register_timeout_handler<F>(handler: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let interval = CHECK_INTERVAL.as_millis() as u64 * TIMER_FREQ / 1000;
+    let next_check = AtomicU64::new(Jiffies::elapsed().as_u64() + interval);
+    timer::register_callback(move || {
+        let now = Jiffies::elapsed().as_u64();
+        let deadline = next_check.load(Ordering::Relaxed);
+        if now < deadline
+            || next_check
+                .compare_exchange(
+                    deadline,
+                    now + interval,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return;
+        }
+        handler();
+    });
+}
+
+/// The deadline of an in-flight block request.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestDeadline {
+    start: Jiffies,
+    timeout: Duration,
+}
+
+impl RequestDeadline {
+    /// Starts a deadline with the current request timeout.
+    pub fn start() -> Self {
+        Self {
+            start: Jiffies::elapsed(),
+            timeout: request_timeout(),
+        }
+    }
+
+    /// Returns the time elapsed since the deadline was (re)started.
+    pub fn elapsed(&self) -> Duration {
+        let now = Jiffies::elapsed().as_u64();
+        Jiffies::new(now.saturating_sub(self.start.as_u64())).as_duration()
+    }
+
+    /// Returns whether the request has passed its deadline.
+    pub fn has_expired(&self) -> bool {
+        !self.timeout.is_zero() && self.elapsed() >= self.timeout
+    }
+
+    /// Restarts the deadline, giving the request another full timeout.
+    pub fn restart(&mut self) {
+        *self = Self::start();
+    }
+}
+
+/// The recovery action that a driver took for a timed-out request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutAction {
+    /// The request turned out to be completed, e.g., its interrupt was lost.
+    Recovered,
+    /// The request was kept in flight or resubmitted with a new deadline.
+    Requeued,
+    /// The request was aborted and its bios were completed with an error.
+    Aborted,
+    /// The controller was reset and the request was failed.
+    Reset,
+}
+
+/// The statistics of the timed-out requests of a block device.
+#[derive(Debug, Default)]
+pub struct HungIoStats {
+    nr_timeouts: AtomicUsize,
+    nr_recovered: AtomicUsize,
+    nr_requeued: AtomicUsize,
+    nr_aborted: AtomicUsize,
+    nr_resets: AtomicUsize,
+}
+
+impl HungIoStats {
+    /// Creates a new set of statistics.
+    pub const fn new() -> Self {
+        Self {
+            nr_timeouts: AtomicUsize::new(0),
+            nr_recovered: AtomicUsize::new(0),
+            nr_requeued: AtomicUsize::new(0),
+            nr_aborted: AtomicUsize::new(0),
+            nr_resets: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records and logs a timed-out request of the device named `device`.
+    pub fn record(&self, device: &str, type_: BioType, elapsed: Duration, action: TimeoutAction) {
+        self.nr_timeouts.fetch_add(1, Ordering::Relaxed);
+        let counter = match action {
+            TimeoutAction::Recovered => &self.nr_recovered,
+            TimeoutAction::Requeued => &self.nr_requeued,
+            TimeoutAction::Aborted => &self.nr_aborted,
+            TimeoutAction::Reset => &self.nr_resets,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        log::warn!(
+            "{}: {:?} request timed out after {} ms, action: {:?}",
+            device,
+            type_,
+            elapsed.as_millis(),
+            action
+        );
+    }
+
+    /// Returns the total number of timed-out requests.
+    pub fn nr_timeouts(&self) -> usize {
+        self.nr_timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of timed-out requests that turned out to be completed.
+    pub fn nr_recovered(&self) -> usize {
+        self.nr_recovered.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of timed-out requests that were requeued.
+    pub fn nr_requeued(&self) -> usize {
+        self.nr_requeued.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of timed-out requests that were aborted.
+    pub fn nr_aborted(&self) -> usize {
+        self.nr_aborted.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of controller resets caused by timed-out requests.
+    pub fn nr_resets(&self) -> usize {
+        self.nr_resets.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::{sync::Arc, vec::Vec};
+
+    use ostd::{prelude::*, sync::SpinLock};
+
+    use super::*;
+    use crate::{
+        bio::{Bio, BioEnqueueError, BioStatus, SubmittedBio},
+        id::Sid,
+        BlockDevice, BlockDeviceMeta,
+    };
+
+    /// A block device whose requests never complete by themselves.
+    #[derive(Debug)]
+    struct StalledDevice {
+        in_flight: SpinLock<Vec<(SubmittedBio, RequestDeadline)>>,
+        stats: HungIoStats,
+    }
+
+    impl StalledDevice {
+        fn new() -> Self {
+            Self {
+                in_flight: SpinLock::new(Vec::new()),
+                stats: HungIoStats::new(),
+            }
+        }
+
+        /// Aborts the in-flight requests that have passed their deadlines.
+        fn handle_timeouts(&self) {
+            self.in_flight
+                .disable_irq()
+                .lock()
+                .retain(|(bio, deadline)| {
+                    if !deadline.has_expired() {
+                        return true;
+                    }
+                    self.stats.record(
+                        "stalled",
+                        bio.type_(),
+                        deadline.elapsed(),
+                        TimeoutAction::Aborted,
+                    );
+                    bio.complete(BioStatus::TimedOut);
+                    false
+                });
+        }
+
+        fn nr_in_flight(&self) -> usize {
+            self.in_flight.disable_irq().lock().len()
+        }
+    }
+
+    impl BlockDevice for StalledDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+            let deadline = RequestDeadline::start();
+            self.in_flight.disable_irq().lock().push((bio, deadline));
+            Ok(())
+        }
+
+        fn metadata(&self) -> BlockDeviceMeta {
+            BlockDeviceMeta {
+                max_nr_segments_per_bio: 1,
+                nr_sectors: 0,
+            }
+        }
+
+        fn hung_io_stats(&self) -> Option<&HungIoStats> {
+            Some(&self.stats)
+        }
+    }
+
+    fn submit_flush(device: &StalledDevice) -> Bio {
+        let bio = Bio::new_dataless(BioType::Flush, Sid::new(0)..Sid::new(0), None);
+        bio.submit(device).unwrap();
+        bio
+    }
+
+    #[ktest]
+    fn stalled_request_times_out() {
+        let device = Arc::new(StalledDevice::new());
+        let weak_device = Arc::downgrade(&device);
+        register_timeout_handler(move || {
+            if let Some(device) = weak_device.upgrade() {
+                device.handle_timeouts();
+            }
+        });
+
+        // The deadline is taken when the request is enqueued, so the default
+        // timeout can be restored right after the submission.
+        set_request_timeout(Duration::from_millis(10));
+        let bio = Bio::new_dataless(BioType::Flush, Sid::new(0)..Sid::new(0), None);
+        let waiter = bio.submit(device.as_ref()).unwrap();
+        set_request_timeout(DEFAULT_REQUEST_TIMEOUT);
+
+        // Without the timeout handler, the waiter would sleep forever.
+        assert_eq!(waiter.wait(), None);
+        assert_eq!(bio.status(), BioStatus::TimedOut);
+        assert_eq!(device.nr_in_flight(), 0);
+
+        let stats = device.hung_io_stats().unwrap();
+        assert_eq!(stats.nr_timeouts(), 1);
+        assert_eq!(stats.nr_aborted(), 1);
+        assert_eq!(stats.nr_resets(), 0);
+    }
+
+    #[ktest]
+    fn request_within_deadline_is_kept() {
+        let device = StalledDevice::new();
+
+        let bio = submit_flush(&device);
+        device.handle_timeouts();
+        assert_eq!(bio.status(), BioStatus::Submit);
+        assert_eq!(device.nr_in_flight(), 1);
+
+        // A zero timeout disables the detection.
+        set_request_timeout(Duration::ZERO);
+        let _bio = submit_flush(&device);
+        set_request_timeout(DEFAULT_REQUEST_TIMEOUT);
+        device.handle_timeouts();
+        assert_eq!(device.nr_in_flight(), 2);
+        assert_eq!(device.stats.nr_timeouts(), 0);
+    }
+}
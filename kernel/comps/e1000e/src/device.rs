@@ -0,0 +1,523 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    hint::spin_loop,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_bigtcp::device::{Checksum, DeviceCapabilities, Medium};
+use aster_network::{
+    AnyNetworkDevice, EthernetAddr, RxBuffer, TxBuffer, VirtioNetError, RX_BUFFER_POOL,
+    TX_BUFFER_POOL_SIZE,
+};
+use log::{debug, info, warn};
+use ostd::{
+    bus::pci::{
+        capability::{msix::CapabilityMsixData, CapabilityData},
+        cfg_space::{Bar, Command},
+        common_device::PciCommonDevice,
+    },
+    io::IoMem,
+    mm::{DmaStream, HasDaddr},
+    sync::{RingBuffer, SpinLock},
+    timer::Jiffies,
+    trap::IrqLine,
+};
+use spin::Once;
+
+use crate::{
+    regs::*,
+    ring::{
+        is_full, next_index, DescRing, RxDesc, TxDesc, RING_SIZE, TX_CMD_EOP, TX_CMD_IFCS,
+        TX_CMD_RS,
+    },
+    E1000eError,
+};
+
+/// The time that the controller takes to finish the reset or the EEPROM read.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The interrupt causes of the RX queue 0, the TX queue 0, and the other causes in the MSI-X
+/// mode (82574 only).
+const INT_RXQ0: u32 = 1 << 20;
+const INT_TXQ0: u32 = 1 << 22;
+const INT_OTHER: u32 = 1 << 24;
+
+static TX_BUFFER_POOL: Once<RingBuffer<DmaStream>> = Once::new();
+
+/// An Intel gigabit Ethernet controller.
+pub struct E1000eDevice {
+    regs: Arc<Registers>,
+    caps: DeviceCapabilities,
+    mac_addr: EthernetAddr,
+    rx_ring: DescRing<RxDesc>,
+    rx_buffers: Vec<Option<RxBuffer>>,
+    /// The index of the next descriptor that the controller completes.
+    rx_next: usize,
+    tx_ring: DescRing<TxDesc>,
+    tx_buffers: Vec<Option<TxBuffer>>,
+    /// The index of the oldest descriptor that is owned by the controller.
+    tx_head: usize,
+    /// The index of the next free descriptor.
+    tx_tail: usize,
+    /// The MSI-X capability, which owns the IRQ line.
+    ///
+    /// The controller is polled by the timer if MSI-X is not used.
+    _msix: Option<CapabilityMsixData>,
+}
+
+/// The registers of a controller, which are shared with the interrupt handler.
+struct Registers {
+    name: String,
+    io_mem: IoMem,
+    mac_type: MacType,
+    is_link_up: AtomicBool,
+}
+
+impl Registers {
+    fn read(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    /// Handles the pending interrupt causes.
+    fn handle_interrupt(&self) {
+        let icr = self.read(self.mac_type.icr());
+        if icr == 0 {
+            return;
+        }
+        // Clear the causes, since the register may not be cleared on read in the MSI-X mode.
+        self.write(self.mac_type.icr(), icr);
+
+        if icr & (INT_LSC | INT_OTHER) != 0 {
+            self.update_link();
+        }
+        if icr & (INT_RX_CAUSES | INT_RXQ0) != 0 {
+            aster_network::raise_receive_softirq();
+        }
+        if icr & (INT_TXDW | INT_TXQ0) != 0 {
+            aster_network::raise_send_softirq();
+        }
+    }
+
+    /// Reads the link status and reports the change.
+    fn update_link(&self) {
+        let is_link_up = self.read(REG_STATUS) & STATUS_LU != 0;
+        if self.is_link_up.swap(is_link_up, Ordering::Relaxed) == is_link_up {
+            return;
+        }
+
+        if is_link_up {
+            info!("[e1000e]: {}: Link is up", self.name);
+            // Packets may have been queued while the link is down.
+            aster_network::raise_send_softirq();
+        } else {
+            info!("[e1000e]: {}: Link is down", self.name);
+        }
+    }
+
+    /// Waits until `f` returns `Some`.
+    fn wait_for<T>(&self, mut f: impl FnMut(&Self) -> Option<T>) -> Result<T, E1000eError> {
+        let deadline = Jiffies::elapsed().as_duration() + TIMEOUT;
+
+        loop {
+            if let Some(value) = f(self) {
+                return Ok(value);
+            }
+            if Jiffies::elapsed().as_duration() >= deadline {
+                return Err(E1000eError::Timeout);
+            }
+            spin_loop();
+        }
+    }
+}
+
+impl E1000eDevice {
+    /// Initializes the controller and registers it to the network layer.
+    pub(crate) fn init(
+        index: usize,
+        pci_device: &PciCommonDevice,
+        mac_type: MacType,
+    ) -> Result<(), E1000eError> {
+        let Some(Bar::Memory(bar)) = pci_device.bar_manager().bar(0).clone() else {
+            return Err(E1000eError::NotSupported);
+        };
+        pci_device.set_command(pci_device.command() | Command::MEMORY_SPACE | Command::BUS_MASTER);
+
+        let regs = Arc::new(Registers {
+            name: format!("e1000e{}", index),
+            io_mem: bar.io_mem().clone(),
+            mac_type,
+            is_link_up: AtomicBool::new(false),
+        });
+
+        Self::reset(&regs)?;
+        let mac_addr = Self::read_mac_addr(&regs);
+        debug!("[e1000e]: {}: MAC address {:x?}", regs.name, mac_addr.0);
+
+        // Accept the packets to the MAC address and the broadcast packets only.
+        let [a0, a1, a2, a3, a4, a5] = mac_addr.0;
+        regs.write(REG_RAL0, u32::from_le_bytes([a0, a1, a2, a3]));
+        regs.write(REG_RAH0, u16::from_le_bytes([a4, a5]) as u32 | RAH_AV);
+        for i in 0..NR_MTA_ENTRIES {
+            regs.write(REG_MTA + i * 4, 0);
+        }
+
+        let ctrl = regs.read(REG_CTRL);
+        regs.write(REG_CTRL, (ctrl | CTRL_SLU | CTRL_ASDE) & !CTRL_PHY_RST);
+
+        let mut device = Self {
+            regs: regs.clone(),
+            caps: init_caps(),
+            mac_addr,
+            rx_ring: DescRing::new()?,
+            rx_buffers: (0..RING_SIZE).map(|_| None).collect(),
+            rx_next: 0,
+            tx_ring: DescRing::new()?,
+            tx_buffers: (0..RING_SIZE).map(|_| None).collect(),
+            tx_head: 0,
+            tx_tail: 0,
+            _msix: None,
+        };
+        device.init_rx();
+        device.init_tx();
+
+        // Only the MSI-X interrupts of 82574 are supported. The other controllers, including the
+        // ones that only support INTx or MSI, are polled by the timer.
+        let msix = if mac_type == MacType::E1000e {
+            pci_device
+                .capabilities()
+                .iter()
+                .find_map(|cap| match cap.capability_data() {
+                    CapabilityData::Msix(msix) => Some(msix.clone()),
+                    _ => None,
+                })
+        } else {
+            None
+        };
+        device._msix = match msix {
+            Some(msix) => Some(Self::init_msix(&regs, msix)?),
+            None => {
+                let cloned_regs = regs.clone();
+                ostd::timer::register_callback(move || cloned_regs.handle_interrupt());
+                None
+            }
+        };
+
+        regs.update_link();
+        if !regs.is_link_up.load(Ordering::Relaxed) {
+            info!("[e1000e]: {}: Link is down", regs.name);
+        }
+
+        aster_network::register_device(regs.name.clone(), Arc::new(SpinLock::new(device)));
+        crate::DEVICE_NAMES.lock().push(regs.name.clone());
+        Ok(())
+    }
+
+    /// Returns whether the link is up.
+    pub fn is_link_up(&self) -> bool {
+        self.regs.is_link_up.load(Ordering::Relaxed)
+    }
+
+    /// Resets the controller and masks all the interrupts.
+    fn reset(regs: &Registers) -> Result<(), E1000eError> {
+        regs.write(regs.mac_type.imc(), u32::MAX);
+
+        let ctrl = regs.read(REG_CTRL);
+        regs.write(REG_CTRL, ctrl | CTRL_RST);
+        regs.wait_for(|regs| (regs.read(REG_CTRL) & CTRL_RST == 0).then_some(()))?;
+
+        // The interrupts are unmasked by the reset.
+        regs.write(regs.mac_type.imc(), u32::MAX);
+        regs.read(regs.mac_type.icr());
+        Ok(())
+    }
+
+    /// Reads the MAC address from the EEPROM.
+    ///
+    /// The MAC address in the first receive address registers, which is loaded from the EEPROM
+    /// by the controller, is used if the EEPROM cannot be read (e.g., there is only the flash).
+    fn read_mac_addr(regs: &Registers) -> EthernetAddr {
+        let read_eeprom = || -> Result<[u32; 3], E1000eError> {
+            let mut values = [0u32; 3];
+            for (word_addr, value) in values.iter_mut().enumerate() {
+                regs.write(REG_EERD, regs.mac_type.eerd_start(word_addr as u8));
+                *value = regs.wait_for(|regs| {
+                    let value = regs.read(REG_EERD);
+                    (value & regs.mac_type.eerd_done() != 0).then_some(value)
+                })?;
+            }
+            Ok(values)
+        };
+        if let Some(addr) = read_eeprom().ok().and_then(mac_addr_from_eerd) {
+            return addr;
+        }
+
+        warn!(
+            "[e1000e]: {}: Failed to read the MAC address from the EEPROM",
+            regs.name
+        );
+        mac_addr_from_ra(regs.read(REG_RAL0), regs.read(REG_RAH0))
+    }
+
+    fn init_rx(&mut self) {
+        let rx_pool = RX_BUFFER_POOL.get().unwrap();
+        for (index, slot) in self.rx_buffers.iter_mut().enumerate() {
+            let rx_buffer = RxBuffer::new(0, rx_pool);
+            self.rx_ring.write(
+                index,
+                &RxDesc {
+                    addr: rx_buffer.daddr() as u64,
+                    ..Default::default()
+                },
+            );
+            *slot = Some(rx_buffer);
+        }
+
+        let regs = &self.regs;
+        let base = regs.mac_type.rx_queue_base();
+        regs.write(base + QUEUE_BAL, self.rx_ring.daddr() as u32);
+        regs.write(base + QUEUE_BAH, (self.rx_ring.daddr() >> 32) as u32);
+        regs.write(base + QUEUE_LEN, self.rx_ring.nbytes());
+        regs.write(base + QUEUE_HEAD, 0);
+        regs.write(base + QUEUE_TAIL, 0);
+        if regs.mac_type == MacType::Igb {
+            regs.write(base + QUEUE_SRRCTL, SRRCTL_BSIZE_2K);
+            let dctl = regs.read(base + QUEUE_DCTL);
+            regs.write(base + QUEUE_DCTL, dctl | DCTL_QUEUE_ENABLE);
+        }
+
+        // The buffer size is 2 KiB, which is the default value of the RCTL.BSIZE field. Long
+        // packets are not accepted, so each packet fits in one buffer.
+        regs.write(REG_RCTL, RCTL_EN | RCTL_BAM | RCTL_SECRC);
+        // Hand all the descriptors except one to the controller, since the ring is considered
+        // empty if the head equals the tail.
+        regs.write(base + QUEUE_TAIL, RING_SIZE as u32 - 1);
+    }
+
+    fn init_tx(&mut self) {
+        let regs = &self.regs;
+        let base = regs.mac_type.tx_queue_base();
+        regs.write(base + QUEUE_BAL, self.tx_ring.daddr() as u32);
+        regs.write(base + QUEUE_BAH, (self.tx_ring.daddr() >> 32) as u32);
+        regs.write(base + QUEUE_LEN, self.tx_ring.nbytes());
+        regs.write(base + QUEUE_HEAD, 0);
+        regs.write(base + QUEUE_TAIL, 0);
+        if regs.mac_type == MacType::Igb {
+            let dctl = regs.read(base + QUEUE_DCTL);
+            regs.write(base + QUEUE_DCTL, dctl | DCTL_QUEUE_ENABLE);
+        } else {
+            regs.write(REG_TIPG, TIPG_COPPER);
+        }
+
+        regs.write(REG_TCTL, TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD);
+    }
+
+    /// Routes all the interrupt causes to the MSI-X vector 0 and unmasks them.
+    fn init_msix(
+        regs: &Arc<Registers>,
+        mut msix: CapabilityMsixData,
+    ) -> Result<CapabilityMsixData, E1000eError> {
+        let irq = IrqLine::alloc().map_err(|_| E1000eError::NoMemory)?;
+        msix.set_interrupt_vector(irq, 0);
+        let cloned_regs = regs.clone();
+        msix.irq_mut(0)
+            .unwrap()
+            .on_active(move |_| cloned_regs.handle_interrupt());
+
+        regs.write(REG_IVAR, IVAR_ALL_TO_VECTOR0);
+        let ctrl_ext = regs.read(REG_CTRL_EXT);
+        regs.write(REG_CTRL_EXT, ctrl_ext | CTRL_EXT_PBA_CLR);
+        regs.write(
+            regs.mac_type.ims(),
+            INT_CAUSES | INT_RXQ0 | INT_TXQ0 | INT_OTHER,
+        );
+
+        Ok(msix)
+    }
+}
+
+impl AnyNetworkDevice for E1000eDevice {
+    fn mac_addr(&self) -> EthernetAddr {
+        self.mac_addr
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.caps.clone()
+    }
+
+    fn can_receive(&self) -> bool {
+        self.rx_ring.is_done(self.rx_next)
+    }
+
+    fn can_send(&self) -> bool {
+        !is_full(self.tx_head, self.tx_tail)
+    }
+
+    fn receive(&mut self) -> Result<RxBuffer, VirtioNetError> {
+        let index = self.rx_next;
+        if !self.rx_ring.is_done(index) {
+            return Err(VirtioNetError::NotReady);
+        }
+
+        let desc = self.rx_ring.read(index);
+        if desc.errors != 0 {
+            debug!(
+                "[e1000e]: {}: Receive error {:#x}",
+                self.regs.name, desc.errors
+            );
+        }
+        let mut rx_buffer = self.rx_buffers[index]
+            .take()
+            .ok_or(VirtioNetError::WrongToken)?;
+        rx_buffer.set_packet_len(desc.length as usize);
+
+        // FIXME: Like the virtio-net driver, we can reuse the returned buffer if the device is
+        // not locked by the network layer when the buffer is consumed.
+        let new_rx_buffer = RxBuffer::new(0, RX_BUFFER_POOL.get().unwrap());
+        self.rx_ring.write(
+            index,
+            &RxDesc {
+                addr: new_rx_buffer.daddr() as u64,
+                ..Default::default()
+            },
+        );
+        self.rx_buffers[index] = Some(new_rx_buffer);
+        self.regs.write(
+            self.regs.mac_type.rx_queue_base() + QUEUE_TAIL,
+            index as u32,
+        );
+        self.rx_next = next_index(index);
+
+        Ok(rx_buffer)
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), VirtioNetError> {
+        self.free_processed_tx_buffers();
+        if !self.can_send() {
+            return Err(VirtioNetError::Busy);
+        }
+
+        let pool = TX_BUFFER_POOL.call_once(|| RingBuffer::new(TX_BUFFER_POOL_SIZE));
+        let tx_buffer = TxBuffer::new(&[0u8; 0], packet, pool);
+
+        let index = self.tx_tail;
+        self.tx_ring.write(
+            index,
+            &TxDesc {
+                addr: tx_buffer.daddr() as u64,
+                length: tx_buffer.nbytes() as u16,
+                cmd: TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RS,
+                ..Default::default()
+            },
+        );
+        self.tx_buffers[index] = Some(tx_buffer);
+        self.tx_tail = next_index(index);
+        self.regs.write(
+            self.regs.mac_type.tx_queue_base() + QUEUE_TAIL,
+            self.tx_tail as u32,
+        );
+
+        Ok(())
+    }
+
+    fn free_processed_tx_buffers(&mut self) {
+        while self.tx_buffers[self.tx_head].is_some() && self.tx_ring.is_done(self.tx_head) {
+            self.tx_buffers[self.tx_head] = None;
+            self.tx_head = next_index(self.tx_head);
+        }
+    }
+
+    fn notify_poll_end(&mut self) {
+        // The tails are advanced when the descriptors are queued, so there is nothing to notify.
+    }
+}
+
+impl Debug for E1000eDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("E1000eDevice")
+            .field("name", &self.regs.name)
+            .field("mac_type", &self.regs.mac_type)
+            .field("mac_addr", &self.mac_addr)
+            .field("is_link_up", &self.is_link_up())
+            .field("rx_next", &self.rx_next)
+            .field("tx_head", &self.tx_head)
+            .field("tx_tail", &self.tx_tail)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Assembles the MAC address from the EERD values of the first three EEPROM words.
+///
+/// Returns `None` if the EEPROM is not programmed.
+fn mac_addr_from_eerd(values: [u32; 3]) -> Option<EthernetAddr> {
+    let mut addr = [0u8; 6];
+    for (bytes, value) in addr.chunks_exact_mut(2).zip(values) {
+        bytes.copy_from_slice(&((value >> 16) as u16).to_le_bytes());
+    }
+    (addr != [0u8; 6] && addr != [0xffu8; 6]).then_some(EthernetAddr(addr))
+}
+
+/// Returns the MAC address in the first receive address registers.
+fn mac_addr_from_ra(ral: u32, rah: u32) -> EthernetAddr {
+    let mut addr = [0u8; 6];
+    addr[..4].copy_from_slice(&ral.to_le_bytes());
+    addr[4..].copy_from_slice(&rah.to_le_bytes()[..2]);
+    EthernetAddr(addr)
+}
+
+fn init_caps() -> DeviceCapabilities {
+    let mut caps = DeviceCapabilities::default();
+
+    caps.max_burst_size = None;
+    caps.medium = Medium::Ethernet;
+    // Jumbo frames are not enabled, so the MTU is the standard Ethernet frame size without the
+    // FCS, which is inserted by the controller.
+    caps.max_transmission_unit = 1514;
+
+    // We do not enable checksum offloading.
+    caps.checksum.tcp = Checksum::Both;
+    caps.checksum.udp = Checksum::Both;
+    caps.checksum.ipv4 = Checksum::Both;
+    caps.checksum.icmpv4 = Checksum::Both;
+
+    caps
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_mac_addr() {
+        // The data of the EEPROM words are in the upper halves of the EERD values.
+        let done = MacType::E1000e.eerd_done();
+        let values = [0x5452_0000 | done, 0x3412_0000 | done, 0x5634_0000 | done];
+        assert_eq!(
+            mac_addr_from_eerd(values).unwrap().0,
+            [0x52, 0x54, 0x12, 0x34, 0x34, 0x56]
+        );
+
+        assert!(mac_addr_from_eerd([done; 3]).is_none());
+        assert!(mac_addr_from_eerd([0xffff_0000 | done; 3]).is_none());
+
+        // Only the lower 16 bits of RAH are the address, and the upper bits are flags.
+        let addr = mac_addr_from_ra(0x3412_5452, RAH_AV | 0x5634);
+        assert_eq!(addr.0, [0x52, 0x54, 0x12, 0x34, 0x34, 0x56]);
+    }
+
+    #[ktest]
+    fn eerd_encoding() {
+        assert_eq!(MacType::E1000.eerd_start(2), 0x201);
+        assert_eq!(MacType::E1000e.eerd_start(2), 0x9);
+        assert_eq!(MacType::Igb.eerd_start(2), 0x9);
+    }
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the Intel gigabit Ethernet controllers.
+//!
+//! The driver supports the 8254x (e1000), 82574 (e1000e), 82576, and I210/I211 (igb)
+//! controllers, which cover the NICs emulated by QEMU and the ones commonly found on the lab
+//! machines. Each controller is registered to the network layer as a network device named
+//! `e1000eX`, where `X` is the index of the controller.
+//!
+//! Only one receive queue and one transmit queue with the legacy descriptors are used, and no
+//! offloading features are enabled.
+//!
+//! Reference: <https://www.intel.com/content/dam/doc/manual/pci-pci-x-family-gbe-controllers-software-dev-manual.pdf>
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod device;
+mod pci;
+mod regs;
+mod ring;
+
+use alloc::{string::String, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+pub use device::E1000eDevice;
+use ostd::sync::SpinLock;
+
+/// The errors of the Intel gigabit Ethernet controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum E1000eError {
+    /// The controller is not supported by the driver.
+    NotSupported,
+    /// The memory for the rings cannot be allocated.
+    NoMemory,
+    /// The controller does not respond in time.
+    Timeout,
+}
+
+static DEVICE_NAMES: SpinLock<Vec<String>> = SpinLock::new(Vec::new());
+
+/// Returns the names of the registered network devices.
+pub fn device_names() -> Vec<String> {
+    DEVICE_NAMES.lock().clone()
+}
+
+#[init_component]
+fn e1000e_init() -> Result<(), ComponentInitError> {
+    pci::init();
+    Ok(())
+}
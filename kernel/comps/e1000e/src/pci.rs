@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI driver of the Intel gigabit Ethernet controllers.
+//!
+//! The controllers are only claimed when the PCI bus is probed, and they are initialized after
+//! that, since the initialization waits for the controllers and registers the network devices.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use log::warn;
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    sync::SpinLock,
+};
+
+use crate::{device::E1000eDevice, regs::MacType};
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(E1000ePciDriver));
+
+    let devices = core::mem::take(&mut *CLAIMED_DEVICES.lock());
+    for (index, (device, mac_type)) in devices.into_iter().enumerate() {
+        if let Err(err) = E1000eDevice::init(index, &device, mac_type) {
+            warn!("[e1000e]: Failed to initialize e1000e{}: {:?}", index, err);
+        }
+    }
+}
+
+const INTEL_VENDOR_ID: u16 = 0x8086;
+
+/// The supported controllers, which are identified by their device IDs.
+const SUPPORTED_DEVICES: &[(u16, MacType)] = &[
+    // 82540EM, which is emulated by QEMU's `e1000` device.
+    (0x100E, MacType::E1000),
+    // 82545EM.
+    (0x100F, MacType::E1000),
+    // 82574L, which is emulated by QEMU's `e1000e` device.
+    (0x10D3, MacType::E1000e),
+    // 82576, which is emulated by QEMU's `igb` device.
+    (0x10C9, MacType::Igb),
+    // I210.
+    (0x1533, MacType::Igb),
+    (0x1536, MacType::Igb),
+    (0x1537, MacType::Igb),
+    (0x1538, MacType::Igb),
+    (0x157B, MacType::Igb),
+    (0x157C, MacType::Igb),
+    // I211.
+    (0x1539, MacType::Igb),
+];
+
+/// The controllers that are claimed but not initialized.
+static CLAIMED_DEVICES: SpinLock<Vec<(PciCommonDevice, MacType)>> = SpinLock::new(Vec::new());
+
+#[derive(Debug)]
+struct E1000ePciDriver;
+
+impl PciDriver for E1000ePciDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.vendor_id != INTEL_VENDOR_ID {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+        let Some(&(_, mac_type)) = SUPPORTED_DEVICES
+            .iter()
+            .find(|(id, _)| *id == device_id.device_id)
+        else {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        };
+
+        CLAIMED_DEVICES.lock().push((device, mac_type));
+        Ok(Arc::new(E1000ePciDevice { device_id }))
+    }
+}
+
+#[derive(Debug)]
+struct E1000ePciDevice {
+    device_id: PciDeviceId,
+}
+
+impl PciDevice for E1000ePciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registers of the Intel gigabit Ethernet controllers.
+//!
+//! The controllers share most of the register layout. The differences that matter to the driver
+//! (i.e., the interrupt registers, the queue registers, and the EEPROM read register) are
+//! described by [`MacType`].
+
+/// Device Control.
+pub(crate) const REG_CTRL: usize = 0x0;
+/// Device Status.
+pub(crate) const REG_STATUS: usize = 0x8;
+/// EEPROM Read.
+pub(crate) const REG_EERD: usize = 0x14;
+/// Extended Device Control.
+pub(crate) const REG_CTRL_EXT: usize = 0x18;
+/// Interrupt Vector Allocation (82574 only).
+pub(crate) const REG_IVAR: usize = 0xE4;
+/// Receive Control.
+pub(crate) const REG_RCTL: usize = 0x100;
+/// Transmit Control.
+pub(crate) const REG_TCTL: usize = 0x400;
+/// Transmit Inter Packet Gap.
+pub(crate) const REG_TIPG: usize = 0x410;
+/// Multicast Table Array.
+pub(crate) const REG_MTA: usize = 0x5200;
+/// The number of the entries in the Multicast Table Array.
+pub(crate) const NR_MTA_ENTRIES: usize = 128;
+/// Receive Address Low of the first entry.
+pub(crate) const REG_RAL0: usize = 0x5400;
+/// Receive Address High of the first entry.
+pub(crate) const REG_RAH0: usize = 0x5404;
+
+// The offsets of the queue registers relative to the base of queue 0.
+/// Descriptor Base Address Low.
+pub(crate) const QUEUE_BAL: usize = 0x0;
+/// Descriptor Base Address High.
+pub(crate) const QUEUE_BAH: usize = 0x4;
+/// Descriptor Ring Length.
+pub(crate) const QUEUE_LEN: usize = 0x8;
+/// Split and Replication Receive Control (igb only).
+pub(crate) const QUEUE_SRRCTL: usize = 0xC;
+/// Descriptor Head.
+pub(crate) const QUEUE_HEAD: usize = 0x10;
+/// Descriptor Tail.
+pub(crate) const QUEUE_TAIL: usize = 0x18;
+/// Descriptor Control.
+pub(crate) const QUEUE_DCTL: usize = 0x28;
+
+pub(crate) const CTRL_ASDE: u32 = 1 << 5;
+pub(crate) const CTRL_SLU: u32 = 1 << 6;
+pub(crate) const CTRL_RST: u32 = 1 << 26;
+pub(crate) const CTRL_PHY_RST: u32 = 1 << 31;
+
+pub(crate) const STATUS_LU: u32 = 1 << 1;
+
+pub(crate) const CTRL_EXT_PBA_CLR: u32 = 1 << 31;
+
+/// Transmit Descriptor Written Back.
+pub(crate) const INT_TXDW: u32 = 1 << 0;
+/// Link Status Change.
+pub(crate) const INT_LSC: u32 = 1 << 2;
+/// Receive Descriptor Minimum Threshold Reached.
+pub(crate) const INT_RXDMT0: u32 = 1 << 4;
+/// Receiver Overrun.
+pub(crate) const INT_RXO: u32 = 1 << 6;
+/// Receiver Timer Interrupt.
+pub(crate) const INT_RXT0: u32 = 1 << 7;
+/// The interrupt causes that the driver handles.
+pub(crate) const INT_CAUSES: u32 = INT_TXDW | INT_LSC | INT_RXDMT0 | INT_RXO | INT_RXT0;
+/// The interrupt causes that indicate received packets.
+pub(crate) const INT_RX_CAUSES: u32 = INT_RXDMT0 | INT_RXO | INT_RXT0;
+
+/// Routes the RX queue 0, the TX queue 0, and the other causes to the MSI-X vector 0.
+pub(crate) const IVAR_ALL_TO_VECTOR0: u32 = 0x0008_0808;
+
+pub(crate) const RCTL_EN: u32 = 1 << 1;
+/// Broadcast Accept Mode.
+pub(crate) const RCTL_BAM: u32 = 1 << 15;
+/// Strip Ethernet CRC.
+pub(crate) const RCTL_SECRC: u32 = 1 << 26;
+
+pub(crate) const TCTL_EN: u32 = 1 << 1;
+/// Pad Short Packets.
+pub(crate) const TCTL_PSP: u32 = 1 << 3;
+/// Collision Threshold, which is the recommended value.
+pub(crate) const TCTL_CT: u32 = 0x0F << 4;
+/// Collision Distance, which is the recommended value for the full-duplex operation.
+pub(crate) const TCTL_COLD: u32 = 0x40 << 12;
+
+/// The recommended inter packet gap for the copper media (IPGT = 10, IPGR1 = 8, IPGR2 = 6).
+pub(crate) const TIPG_COPPER: u32 = 10 | (8 << 10) | (6 << 20);
+
+/// The receive buffer size in the SRRCTL register, which is in the unit of 1 KiB.
+pub(crate) const SRRCTL_BSIZE_2K: u32 = 2;
+/// Enables the queue (igb only).
+pub(crate) const DCTL_QUEUE_ENABLE: u32 = 1 << 25;
+
+pub(crate) const RAH_AV: u32 = 1 << 31;
+
+/// The family of a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MacType {
+    /// The 8254x controllers.
+    E1000,
+    /// The 82574 controllers.
+    E1000e,
+    /// The 82576 and I210/I211 controllers.
+    Igb,
+}
+
+impl MacType {
+    /// Returns the offset of the Interrupt Cause Read register.
+    pub(crate) fn icr(self) -> usize {
+        match self {
+            Self::E1000 | Self::E1000e => 0xC0,
+            Self::Igb => 0x1500,
+        }
+    }
+
+    /// Returns the offset of the Interrupt Mask Set/Read register.
+    pub(crate) fn ims(self) -> usize {
+        match self {
+            Self::E1000 | Self::E1000e => 0xD0,
+            Self::Igb => 0x1508,
+        }
+    }
+
+    /// Returns the offset of the Interrupt Mask Clear register.
+    pub(crate) fn imc(self) -> usize {
+        match self {
+            Self::E1000 | Self::E1000e => 0xD8,
+            Self::Igb => 0x150C,
+        }
+    }
+
+    /// Returns the base offset of the registers of the receive queue 0.
+    pub(crate) fn rx_queue_base(self) -> usize {
+        match self {
+            Self::E1000 | Self::E1000e => 0x2800,
+            Self::Igb => 0xC000,
+        }
+    }
+
+    /// Returns the base offset of the registers of the transmit queue 0.
+    pub(crate) fn tx_queue_base(self) -> usize {
+        match self {
+            Self::E1000 | Self::E1000e => 0x3800,
+            Self::Igb => 0xE000,
+        }
+    }
+
+    /// Returns the EERD value that starts reading the EEPROM word at `addr`.
+    pub(crate) fn eerd_start(self, addr: u8) -> u32 {
+        match self {
+            Self::E1000 => ((addr as u32) << 8) | 1,
+            Self::E1000e | Self::Igb => ((addr as u32) << 2) | 1,
+        }
+    }
+
+    /// Returns the bit in the EERD register that indicates the read is done.
+    pub(crate) fn eerd_done(self) -> u32 {
+        match self {
+            Self::E1000 => 1 << 4,
+            Self::E1000e | Self::Igb => 1 << 1,
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The descriptor rings of the Intel gigabit Ethernet controllers.
+//!
+//! A descriptor ring is a circular buffer in the memory shared with the controller. The host
+//! hands the descriptors to the controller by advancing the tail register, and the controller
+//! reports the completed descriptors by setting the DD bit in their status fields.
+
+use core::{
+    mem::{offset_of, size_of},
+    sync::atomic::{fence, Ordering},
+};
+
+use ostd::{
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+    Pod,
+};
+
+use crate::E1000eError;
+
+/// The number of the descriptors in each ring.
+///
+/// The size of a ring must be a multiple of 128 bytes, i.e., 8 descriptors.
+pub(crate) const RING_SIZE: usize = 64;
+
+/// Descriptor Done.
+pub(crate) const DESC_STATUS_DD: u8 = 1 << 0;
+/// End of Packet.
+pub(crate) const DESC_STATUS_EOP: u8 = 1 << 1;
+
+/// End of Packet.
+pub(crate) const TX_CMD_EOP: u8 = 1 << 0;
+/// Insert FCS.
+pub(crate) const TX_CMD_IFCS: u8 = 1 << 1;
+/// Report Status.
+pub(crate) const TX_CMD_RS: u8 = 1 << 3;
+
+/// A legacy receive descriptor.
+#[derive(Debug, Clone, Copy, Default, Pod)]
+#[repr(C)]
+pub(crate) struct RxDesc {
+    pub(crate) addr: u64,
+    pub(crate) length: u16,
+    pub(crate) checksum: u16,
+    pub(crate) status: u8,
+    pub(crate) errors: u8,
+    pub(crate) special: u16,
+}
+
+/// A legacy transmit descriptor.
+#[derive(Debug, Clone, Copy, Default, Pod)]
+#[repr(C)]
+pub(crate) struct TxDesc {
+    pub(crate) addr: u64,
+    pub(crate) length: u16,
+    pub(crate) cso: u8,
+    pub(crate) cmd: u8,
+    pub(crate) status: u8,
+    pub(crate) css: u8,
+    pub(crate) special: u16,
+}
+
+/// Returns the index of the descriptor that follows the one at `index`.
+pub(crate) fn next_index(index: usize) -> usize {
+    (index + 1) % RING_SIZE
+}
+
+/// Returns whether no more descriptors can be handed to the controller.
+///
+/// The controller considers the ring empty if the head equals the tail, so one descriptor is
+/// always left unused.
+pub(crate) fn is_full(head: usize, tail: usize) -> bool {
+    next_index(tail) == head
+}
+
+/// A descriptor ring, whose descriptors are of type `D`.
+#[derive(Debug)]
+pub(crate) struct DescRing<D> {
+    ring: DmaCoherent,
+    _phantom: core::marker::PhantomData<D>,
+}
+
+/// The descriptors whose status fields can be polled.
+pub(crate) trait Desc: Pod {
+    const STATUS_OFFSET: usize;
+}
+
+impl Desc for RxDesc {
+    const STATUS_OFFSET: usize = offset_of!(RxDesc, status);
+}
+
+impl Desc for TxDesc {
+    const STATUS_OFFSET: usize = offset_of!(TxDesc, status);
+}
+
+impl<D: Desc> DescRing<D> {
+    pub(crate) fn new() -> Result<Self, E1000eError> {
+        let nbytes = RING_SIZE * size_of::<D>();
+        let segment = FrameAllocOptions::new()
+            .alloc_segment(nbytes.div_ceil(PAGE_SIZE))
+            .map_err(|_| E1000eError::NoMemory)?;
+        let ring = DmaCoherent::map(segment.into(), true).map_err(|_| E1000eError::NoMemory)?;
+        Ok(Self {
+            ring,
+            _phantom: core::marker::PhantomData,
+        })
+    }
+
+    /// Returns the device address of the ring.
+    pub(crate) fn daddr(&self) -> u64 {
+        self.ring.daddr() as u64
+    }
+
+    /// Returns the length of the ring in bytes.
+    pub(crate) fn nbytes(&self) -> u32 {
+        (RING_SIZE * size_of::<D>()) as u32
+    }
+
+    /// Returns whether the controller has completed the descriptor at `index`.
+    pub(crate) fn is_done(&self, index: usize) -> bool {
+        let status: u8 = self
+            .ring
+            .read_once(index * size_of::<D>() + D::STATUS_OFFSET)
+            .unwrap();
+        status & DESC_STATUS_DD != 0
+    }
+
+    /// Reads the descriptor at `index`.
+    pub(crate) fn read(&self, index: usize) -> D {
+        // Read the other fields after the status field.
+        fence(Ordering::SeqCst);
+        self.ring.read_val(index * size_of::<D>()).unwrap()
+    }
+
+    /// Writes the descriptor at `index`.
+    pub(crate) fn write(&self, index: usize, desc: &D) {
+        self.ring.write_val(index * size_of::<D>(), desc).unwrap();
+        // Make the descriptor visible to the controller before advancing the tail.
+        fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn index_arithmetic() {
+        assert_eq!(next_index(0), 1);
+        assert_eq!(next_index(RING_SIZE - 2), RING_SIZE - 1);
+        assert_eq!(next_index(RING_SIZE - 1), 0);
+
+        assert!(!is_full(0, 0));
+        assert!(!is_full(0, RING_SIZE - 2));
+        assert!(is_full(0, RING_SIZE - 1));
+        assert!(is_full(5, 4));
+        assert!(!is_full(5, 5));
+
+        // At most `RING_SIZE - 1` descriptors are in flight, starting from any head.
+        for head in [0, 1, RING_SIZE / 2, RING_SIZE - 1] {
+            let mut tail = head;
+            let mut nr_queued = 0;
+            while !is_full(head, tail) {
+                tail = next_index(tail);
+                nr_queued += 1;
+            }
+            assert_eq!(nr_queued, RING_SIZE - 1);
+        }
+    }
+
+    #[ktest]
+    fn layout() {
+        assert_eq!(size_of::<RxDesc>(), 16);
+        assert_eq!(size_of::<TxDesc>(), 16);
+        assert_eq!(RxDesc::STATUS_OFFSET, 12);
+        assert_eq!(TxDesc::STATUS_OFFSET, 12);
+        assert_eq!((RING_SIZE * size_of::<RxDesc>()) % 128, 0);
+    }
+
+    #[ktest]
+    fn parse_descriptors() {
+        // A receive descriptor written back by the controller.
+        let mut bytes = [0u8; 16];
+        bytes[0..8].copy_from_slice(&0x1234_5000u64.to_le_bytes());
+        bytes[8..10].copy_from_slice(&60u16.to_le_bytes());
+        bytes[12] = DESC_STATUS_DD | DESC_STATUS_EOP;
+        bytes[13] = 0x80;
+        let desc = RxDesc::from_bytes(&bytes);
+        assert_eq!(desc.addr, 0x1234_5000);
+        assert_eq!(desc.length, 60);
+        assert_eq!(desc.status, DESC_STATUS_DD | DESC_STATUS_EOP);
+        assert_eq!(desc.errors, 0x80);
+
+        let desc = TxDesc {
+            addr: 0x1234_6000,
+            length: 42,
+            cmd: TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RS,
+            ..Default::default()
+        };
+        let bytes = desc.as_bytes();
+        assert_eq!(&bytes[0..8], &0x1234_6000u64.to_le_bytes());
+        assert_eq!(&bytes[8..10], &42u16.to_le_bytes());
+        assert_eq!(bytes[11], 0b1011);
+        assert_eq!(bytes[12], 0);
+    }
+
+    #[ktest]
+    fn poll_status() {
+        let ring = DescRing::<TxDesc>::new().unwrap();
+        assert_eq!(ring.nbytes(), 1024);
+
+        let last = RING_SIZE - 1;
+        ring.write(
+            last,
+            &TxDesc {
+                length: 42,
+                ..Default::default()
+            },
+        );
+        assert!(!ring.is_done(last));
+
+        // The controller sets the DD bit after sending the packet.
+        ring.write(
+            last,
+            &TxDesc {
+                length: 42,
+                status: DESC_STATUS_DD,
+                ..Default::default()
+            },
+        );
+        assert!(ring.is_done(last));
+        assert!(!ring.is_done(last - 1));
+        assert!(!ring.is_done(0));
+        assert_eq!(ring.read(last).length, 42);
+    }
+}
@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! GPIO chips, i.e., the controllers of GPIO lines.
+
+use core::fmt::Debug;
+
+use crate::GpioError;
+
+/// A GPIO chip, which controls a number of GPIO lines.
+///
+/// The lines are identified by their offsets, which range from zero to [`GpioChip::ngpio`]
+/// (exclusive). The offsets passed to the methods are checked by the callers.
+///
+/// The values here are the physical levels of the lines. The active-low lines are handled by
+/// the subsystem.
+///
+/// The methods may be called with local IRQs disabled (e.g., when detecting the edges of the
+/// lines), so they must not sleep.
+pub trait GpioChip: Send + Sync + Debug {
+    /// Returns the label of the chip.
+    fn label(&self) -> &str;
+
+    /// Returns the number of the lines.
+    fn ngpio(&self) -> u32;
+
+    /// Returns the name of the line, if the line is named.
+    fn line_name(&self, _offset: u32) -> Option<&str> {
+        None
+    }
+
+    /// Returns the direction of the line.
+    fn direction(&self, offset: u32) -> Result<GpioDirection, GpioError>;
+
+    /// Configures the line as an input.
+    fn direction_input(&self, offset: u32) -> Result<(), GpioError>;
+
+    /// Configures the line as an output with the initial value.
+    fn direction_output(&self, offset: u32, value: bool) -> Result<(), GpioError>;
+
+    /// Returns the value of the line.
+    fn get(&self, offset: u32) -> Result<bool, GpioError>;
+
+    /// Sets the value of the output line.
+    fn set(&self, offset: u32, value: bool) -> Result<(), GpioError>;
+
+    /// Configures the bias of the line.
+    fn set_bias(&self, _offset: u32, bias: GpioBias) -> Result<(), GpioError> {
+        match bias {
+            GpioBias::AsIs => Ok(()),
+            _ => Err(GpioError::NotSupported),
+        }
+    }
+
+    /// Configures the drive of the output line.
+    fn set_drive(&self, _offset: u32, drive: GpioDrive) -> Result<(), GpioError> {
+        match drive {
+            GpioDrive::PushPull => Ok(()),
+            _ => Err(GpioError::NotSupported),
+        }
+    }
+}
+
+/// The direction of a GPIO line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioDirection {
+    Input,
+    Output,
+}
+
+/// The bias of a GPIO line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioBias {
+    /// Keeps the bias configured by the firmware.
+    AsIs,
+    PullUp,
+    PullDown,
+    Disabled,
+}
+
+/// The drive of a GPIO output line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioDrive {
+    PushPull,
+    OpenDrain,
+    OpenSource,
+}
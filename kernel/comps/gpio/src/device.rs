@@ -0,0 +1,422 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of GPIO chips.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::time::Duration;
+
+use log::info;
+use ostd::sync::Mutex;
+
+use crate::{
+    GpioChip, GpioDirection, GpioError, LineFlags, LineInfo, LineRequest, LineSettings, LINES_MAX,
+};
+
+/// A registered GPIO chip.
+///
+/// The device keeps track of the lines requested from the chip, so that each line is used by at
+/// most one consumer.
+#[derive(Debug)]
+pub struct GpioDevice {
+    index: u32,
+    chip: Arc<dyn GpioChip>,
+    lines: Mutex<Vec<Option<LineOwner>>>,
+}
+
+#[derive(Debug)]
+struct LineOwner {
+    consumer: String,
+    settings: LineSettings,
+}
+
+impl GpioDevice {
+    /// Returns the index of the device, which is `N` in `gpiochipN`.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the name of the device.
+    pub fn name(&self) -> String {
+        alloc::format!("gpiochip{}", self.index)
+    }
+
+    pub fn chip(&self) -> &Arc<dyn GpioChip> {
+        &self.chip
+    }
+
+    /// Returns the information of the line.
+    pub fn line_info(&self, offset: u32) -> Result<LineInfo, GpioError> {
+        self.check_offset(offset)?;
+
+        let name = self.chip.line_name(offset).unwrap_or("").to_string();
+        let lines = self.lines.lock();
+        let Some(owner) = &lines[offset as usize] else {
+            let flags = match self.chip.direction(offset)? {
+                GpioDirection::Input => LineFlags::INPUT,
+                GpioDirection::Output => LineFlags::OUTPUT,
+            };
+            return Ok(LineInfo {
+                name,
+                consumer: String::new(),
+                offset,
+                flags,
+                debounce: Duration::ZERO,
+            });
+        };
+
+        let mut flags = owner.settings.flags | LineFlags::USED;
+        // The direction is reported even if it is left as is.
+        if !flags.intersects(LineFlags::INPUT | LineFlags::OUTPUT) {
+            flags |= match self.chip.direction(offset)? {
+                GpioDirection::Input => LineFlags::INPUT,
+                GpioDirection::Output => LineFlags::OUTPUT,
+            };
+        }
+        Ok(LineInfo {
+            name,
+            consumer: owner.consumer.clone(),
+            offset,
+            flags,
+            debounce: owner.settings.debounce,
+        })
+    }
+
+    /// Requests the lines with their settings.
+    ///
+    /// The lines are released when the returned request is dropped. `event_capacity` is the
+    /// maximum number of the pending edge events of the request.
+    pub fn request_lines(
+        self: &Arc<Self>,
+        consumer: &str,
+        lines: &[(u32, LineSettings)],
+        event_capacity: usize,
+    ) -> Result<LineRequest, GpioError> {
+        if lines.is_empty() || lines.len() > LINES_MAX || event_capacity == 0 {
+            return Err(GpioError::InvalidArgs);
+        }
+        for (i, (offset, settings)) in lines.iter().enumerate() {
+            self.check_offset(*offset)?;
+            if lines[..i].iter().any(|(other, _)| other == offset) {
+                return Err(GpioError::InvalidArgs);
+            }
+            settings.validate()?;
+        }
+
+        let mut owners = self.lines.lock();
+        if lines
+            .iter()
+            .any(|(offset, _)| owners[*offset as usize].is_some())
+        {
+            return Err(GpioError::Busy);
+        }
+        for (offset, settings) in lines {
+            self.apply_settings(*offset, settings)?;
+        }
+        for (offset, settings) in lines {
+            owners[*offset as usize] = Some(LineOwner {
+                consumer: consumer.to_string(),
+                settings: *settings,
+            });
+        }
+        drop(owners);
+
+        Ok(LineRequest::new(self.clone(), lines, event_capacity))
+    }
+
+    /// Returns the settings of the requested line.
+    pub(crate) fn settings(&self, offset: u32) -> LineSettings {
+        self.lines.lock()[offset as usize]
+            .as_ref()
+            .unwrap()
+            .settings
+    }
+
+    /// Changes the settings of the requested lines.
+    pub(crate) fn reconfigure(&self, lines: &[(u32, LineSettings)]) -> Result<(), GpioError> {
+        for (_, settings) in lines {
+            settings.validate()?;
+        }
+
+        let mut owners = self.lines.lock();
+        for (offset, settings) in lines {
+            self.apply_settings(*offset, settings)?;
+            owners[*offset as usize].as_mut().unwrap().settings = *settings;
+        }
+        Ok(())
+    }
+
+    /// Releases the requested lines.
+    pub(crate) fn release(&self, offsets: &[u32]) {
+        let mut owners = self.lines.lock();
+        for offset in offsets {
+            owners[*offset as usize] = None;
+        }
+    }
+
+    fn apply_settings(&self, offset: u32, settings: &LineSettings) -> Result<(), GpioError> {
+        match settings.direction() {
+            Some(GpioDirection::Input) => {
+                self.chip.set_bias(offset, settings.bias())?;
+                self.chip.direction_input(offset)
+            }
+            Some(GpioDirection::Output) => {
+                self.chip.set_drive(offset, settings.drive())?;
+                self.chip.set_bias(offset, settings.bias())?;
+                let value = settings.output_value ^ settings.is_active_low();
+                self.chip.direction_output(offset, value)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_offset(&self, offset: u32) -> Result<(), GpioError> {
+        if offset >= self.chip.ngpio() {
+            return Err(GpioError::InvalidArgs);
+        }
+        Ok(())
+    }
+}
+
+/// Registers a chip and returns its device.
+pub fn register_chip(chip: Arc<dyn GpioChip>) -> Arc<GpioDevice> {
+    let mut devices = GPIO_DEVICES.lock();
+
+    let index = devices.last_key_value().map_or(0, |(index, _)| index + 1);
+    let device = Arc::new(GpioDevice {
+        index,
+        lines: Mutex::new((0..chip.ngpio()).map(|_| None).collect()),
+        chip,
+    });
+    info!(
+        "[GPIO]: Registered {}: {} with {} lines",
+        device.name(),
+        device.chip.label(),
+        device.chip.ngpio()
+    );
+    devices.insert(index, device.clone());
+
+    device
+}
+
+/// Returns the device of the index.
+pub fn get_device(index: u32) -> Option<Arc<GpioDevice>> {
+    GPIO_DEVICES.lock().get(&index).cloned()
+}
+
+/// Returns all the devices.
+pub fn all_devices() -> Vec<Arc<GpioDevice>> {
+    GPIO_DEVICES.lock().values().cloned().collect()
+}
+
+static GPIO_DEVICES: Mutex<BTreeMap<u32, Arc<GpioDevice>>> = Mutex::new(BTreeMap::new());
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{event::sample_all, LineEventId};
+
+    const NGPIO: u32 = 8;
+
+    /// A chip whose input levels are driven by the test.
+    #[derive(Debug, Default)]
+    struct MockChip {
+        levels: AtomicU64,
+        outputs: AtomicU64,
+    }
+
+    impl MockChip {
+        fn drive_input(&self, offset: u32, value: bool) {
+            if value {
+                self.levels.fetch_or(1 << offset, Ordering::Relaxed);
+            } else {
+                self.levels.fetch_and(!(1 << offset), Ordering::Relaxed);
+            }
+        }
+
+        fn is_output(&self, offset: u32) -> bool {
+            self.outputs.load(Ordering::Relaxed) & (1 << offset) != 0
+        }
+    }
+
+    impl GpioChip for MockChip {
+        fn label(&self) -> &str {
+            "mock"
+        }
+
+        fn ngpio(&self) -> u32 {
+            NGPIO
+        }
+
+        fn line_name(&self, offset: u32) -> Option<&str> {
+            (offset == 0).then_some("led")
+        }
+
+        fn direction(&self, offset: u32) -> Result<GpioDirection, GpioError> {
+            if self.is_output(offset) {
+                Ok(GpioDirection::Output)
+            } else {
+                Ok(GpioDirection::Input)
+            }
+        }
+
+        fn direction_input(&self, offset: u32) -> Result<(), GpioError> {
+            self.outputs.fetch_and(!(1 << offset), Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn direction_output(&self, offset: u32, value: bool) -> Result<(), GpioError> {
+            self.outputs.fetch_or(1 << offset, Ordering::Relaxed);
+            self.set(offset, value)
+        }
+
+        fn get(&self, offset: u32) -> Result<bool, GpioError> {
+            Ok(self.levels.load(Ordering::Relaxed) & (1 << offset) != 0)
+        }
+
+        fn set(&self, offset: u32, value: bool) -> Result<(), GpioError> {
+            if !self.is_output(offset) {
+                return Err(GpioError::PermissionDenied);
+            }
+            self.drive_input(offset, value);
+            Ok(())
+        }
+    }
+
+    fn register_mock() -> (Arc<MockChip>, Arc<GpioDevice>) {
+        let chip = Arc::new(MockChip::default());
+        let device = register_chip(chip.clone());
+        (chip, device)
+    }
+
+    fn settings(flags: LineFlags) -> LineSettings {
+        LineSettings {
+            flags,
+            ..Default::default()
+        }
+    }
+
+    #[ktest]
+    fn register_and_request() {
+        let (chip, device) = register_mock();
+        assert!(Arc::ptr_eq(&get_device(device.index()).unwrap(), &device));
+
+        let info = device.line_info(0).unwrap();
+        assert_eq!(info.name, "led");
+        assert!(info.consumer.is_empty());
+        assert_eq!(info.flags, LineFlags::INPUT);
+
+        let active_low = LineSettings {
+            flags: LineFlags::OUTPUT | LineFlags::ACTIVE_LOW,
+            output_value: true,
+            ..Default::default()
+        };
+        let lines = [
+            (1, settings(LineFlags::OUTPUT)),
+            (2, active_low),
+            (3, settings(LineFlags::INPUT)),
+        ];
+        let request = device.request_lines("test", &lines, 16).unwrap();
+        // The active-low line is driven low to output a logical one.
+        assert_eq!(chip.get(2), Ok(false));
+        assert_eq!(request.get_values(0b111), Ok(0b010));
+
+        request.set_values(0b001, 0b011).unwrap();
+        assert_eq!(chip.get(1), Ok(true));
+        assert_eq!(chip.get(2), Ok(true));
+        chip.drive_input(3, true);
+        assert_eq!(request.get_values(0b111), Ok(0b101));
+
+        let info = device.line_info(2).unwrap();
+        assert_eq!(info.consumer, "test");
+        assert_eq!(info.flags, active_low.flags | LineFlags::USED);
+
+        drop(request);
+        assert!(device.line_info(2).unwrap().consumer.is_empty());
+    }
+
+    #[ktest]
+    fn error_paths() {
+        let (_chip, device) = register_mock();
+        let input = settings(LineFlags::INPUT);
+
+        let invalid_requests: [&[(u32, LineSettings)]; 4] = [
+            &[],
+            &[(NGPIO, input)],
+            &[(0, input), (0, input)],
+            &[(0, settings(LineFlags::INPUT | LineFlags::OUTPUT))],
+        ];
+        for lines in invalid_requests {
+            assert_eq!(
+                device.request_lines("test", lines, 16).unwrap_err(),
+                GpioError::InvalidArgs
+            );
+        }
+        assert_eq!(
+            device.request_lines("test", &[(0, input)], 0).unwrap_err(),
+            GpioError::InvalidArgs
+        );
+
+        // The bias is not supported by the chip, so the line is not requested.
+        let pull_up = settings(LineFlags::INPUT | LineFlags::BIAS_PULL_UP);
+        assert_eq!(
+            device
+                .request_lines("test", &[(0, pull_up)], 16)
+                .unwrap_err(),
+            GpioError::NotSupported
+        );
+
+        let request = device.request_lines("test", &[(0, input)], 16).unwrap();
+        assert_eq!(
+            device
+                .request_lines("other", &[(0, input)], 16)
+                .unwrap_err(),
+            GpioError::Busy
+        );
+
+        assert_eq!(request.set_values(1, 1), Err(GpioError::PermissionDenied));
+        assert_eq!(request.get_values(0), Err(GpioError::InvalidArgs));
+        assert_eq!(request.get_values(0b10), Err(GpioError::InvalidArgs));
+        assert_eq!(request.reconfigure(&[]), Err(GpioError::InvalidArgs));
+    }
+
+    #[ktest]
+    fn edge_events() {
+        let (chip, device) = register_mock();
+        let flags = LineFlags::INPUT | LineFlags::EDGES;
+        let request = device
+            .request_lines("test", &[(4, settings(flags))], 2)
+            .unwrap();
+        assert!(!request.has_events());
+
+        chip.drive_input(4, true);
+        sample_all();
+        chip.drive_input(4, false);
+        sample_all();
+
+        let event = request.pop_event().unwrap();
+        assert_eq!((event.id, event.offset), (LineEventId::RisingEdge, 4));
+        assert_eq!((event.seqno, event.line_seqno), (1, 1));
+        let event = request.pop_event().unwrap();
+        assert_eq!((event.id, event.seqno), (LineEventId::FallingEdge, 2));
+        assert!(request.pop_event().is_none());
+
+        // The oldest events are discarded if too many events are pending.
+        for value in [true, false, true] {
+            chip.drive_input(4, value);
+            sample_all();
+        }
+        let seqnos = core::iter::from_fn(|| request.pop_event())
+            .map(|event| event.seqno)
+            .collect::<Vec<_>>();
+        assert_eq!(seqnos, [4, 5]);
+    }
+}
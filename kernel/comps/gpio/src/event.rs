@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The edge detection of GPIO lines.
+//!
+//! The edges are detected by sampling the lines on every timer interrupt, so the chips do not
+//! need to deliver interrupts. As a result, the edges that are shorter than a tick may be missed,
+//! and the timestamps have the precision of a tick.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{fmt::Debug, time::Duration};
+
+use ostd::{
+    sync::{LocalIrqDisabled, RingBuffer, SpinLock},
+    timer::Jiffies,
+};
+
+use crate::{GpioChip, LineFlags, LineSettings};
+
+/// An edge event of a GPIO line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEvent {
+    /// The time when the edge is detected, which is measured from the boot.
+    pub timestamp: Duration,
+    pub id: LineEventId,
+    pub offset: u32,
+    /// The sequence number of the event among all the events of the request.
+    pub seqno: u32,
+    /// The sequence number of the event among the events of the line.
+    pub line_seqno: u32,
+}
+
+/// The type of an edge event.
+///
+/// The values are the same as Linux's `enum gpio_v2_line_event_id`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEventId {
+    RisingEdge = 1,
+    FallingEdge = 2,
+}
+
+/// The edge detector of the lines in a request.
+pub(crate) struct EdgeDetector {
+    chip: Arc<dyn GpioChip>,
+    state: SpinLock<DetectorState, LocalIrqDisabled>,
+    /// The detected events. Like Linux, the oldest event is discarded if the buffer is full.
+    events: RingBuffer<LineEvent>,
+}
+
+struct DetectorState {
+    lines: Vec<DetectedLine>,
+    seqno: u32,
+    notifier: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+struct DetectedLine {
+    offset: u32,
+    edges: LineFlags,
+    is_active_low: bool,
+    debounce: Duration,
+    /// The last stable logical value.
+    value: bool,
+    /// The time when the line starts to change, if the change is still being debounced.
+    changing_since: Option<Duration>,
+    seqno: u32,
+}
+
+impl EdgeDetector {
+    /// Creates an edge detector with the capacity of the event buffer.
+    ///
+    /// The capacity is rounded up to a power of two, which is also what Linux does.
+    pub(crate) fn new(chip: Arc<dyn GpioChip>, event_capacity: usize) -> Arc<Self> {
+        let state = DetectorState {
+            lines: Vec::new(),
+            seqno: 0,
+            notifier: None,
+        };
+        let detector = Arc::new(Self {
+            chip,
+            state: SpinLock::new(state),
+            events: RingBuffer::new_overwrite(event_capacity.next_power_of_two()),
+        });
+
+        DETECTORS.lock().push(detector.clone());
+        detector
+    }
+
+    /// Stops detecting the edges.
+    pub(crate) fn remove(self: &Arc<Self>) {
+        DETECTORS
+            .lock()
+            .retain(|detector| !Arc::ptr_eq(detector, self));
+    }
+
+    /// Resets the lines to detect with their settings.
+    ///
+    /// The edges of a line are detected only if the line is an input with some edge flags.
+    pub(crate) fn reset_lines(&self, lines: &[(u32, LineSettings)]) {
+        let mut state = self.state.lock();
+
+        let old_lines = core::mem::take(&mut state.lines);
+        state.lines = lines
+            .iter()
+            .map(|(offset, settings)| {
+                let edges = settings.flags & LineFlags::EDGES;
+                let is_active_low = settings.is_active_low();
+                let value = self.chip.get(*offset).unwrap_or(false) ^ is_active_low;
+                // The sequence numbers continue if the edges were detected before.
+                let seqno = old_lines
+                    .iter()
+                    .find(|line| line.offset == *offset && !line.edges.is_empty())
+                    .map_or(0, |line| line.seqno);
+                DetectedLine {
+                    offset: *offset,
+                    edges,
+                    is_active_low,
+                    debounce: settings.debounce,
+                    value,
+                    changing_since: None,
+                    seqno,
+                }
+            })
+            .collect();
+    }
+
+    /// Sets the function to call when new events arrive.
+    ///
+    /// The function is called with local IRQs disabled.
+    pub(crate) fn set_notifier(&self, notifier: Box<dyn Fn() + Send + Sync>) {
+        self.state.lock().notifier = Some(notifier);
+    }
+
+    pub(crate) fn pop_event(&self) -> Option<LineEvent> {
+        self.events.pop()
+    }
+
+    pub(crate) fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    fn sample(&self, now: Duration) {
+        let mut state = self.state.lock();
+        let DetectorState {
+            lines,
+            seqno,
+            notifier,
+        } = &mut *state;
+
+        let mut has_new_events = false;
+        for line in lines.iter_mut() {
+            if line.edges.is_empty() {
+                continue;
+            }
+            let Ok(raw_value) = self.chip.get(line.offset) else {
+                continue;
+            };
+
+            let value = raw_value ^ line.is_active_low;
+            if value == line.value {
+                line.changing_since = None;
+                continue;
+            }
+            if !line.debounce.is_zero() {
+                let changing_since = *line.changing_since.get_or_insert(now);
+                if now - changing_since < line.debounce {
+                    continue;
+                }
+            }
+            line.value = value;
+            line.changing_since = None;
+
+            let (id, edge) = if value {
+                (LineEventId::RisingEdge, LineFlags::EDGE_RISING)
+            } else {
+                (LineEventId::FallingEdge, LineFlags::EDGE_FALLING)
+            };
+            if !line.edges.contains(edge) {
+                continue;
+            }
+
+            *seqno = seqno.wrapping_add(1);
+            line.seqno = line.seqno.wrapping_add(1);
+            // The events are pushed with the state locked, so no push can be in progress and
+            // the push always succeeds by discarding the oldest event.
+            let _ = self.events.push(LineEvent {
+                timestamp: now,
+                id,
+                offset: line.offset,
+                seqno: *seqno,
+                line_seqno: line.seqno,
+            });
+            has_new_events = true;
+        }
+
+        if has_new_events {
+            if let Some(notifier) = notifier {
+                notifier();
+            }
+        }
+    }
+}
+
+impl Debug for EdgeDetector {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EdgeDetector")
+            .field("chip", &self.chip)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The edge detectors of all the requests.
+static DETECTORS: SpinLock<Vec<Arc<EdgeDetector>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// Samples the lines of all the requests, which is called on every timer interrupt.
+pub(crate) fn sample_all() {
+    let now = Jiffies::elapsed().as_duration();
+    for detector in DETECTORS.lock().iter() {
+        detector.sample(now);
+    }
+}
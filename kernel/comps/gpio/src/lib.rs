@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The GPIO subsystem of Asterinas.
+//!
+//! The subsystem follows the model of Linux:
+//!  - A chip ([`GpioChip`]) is the controller of some GPIO lines. Each chip is registered as a
+//!    device ([`GpioDevice`]), which is identified by its index.
+//!  - A request ([`LineRequest`]) holds some lines of a device exclusively. The lines are
+//!    configured by their settings ([`LineSettings`]), and their values are read and written
+//!    through the request. The edges of the input lines are reported as events ([`LineEvent`]).
+//!
+//! Userspace can access the devices via `/dev/gpiochipN`, which is implemented by the kernel on
+//! top of [`get_device`].
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod chip;
+mod device;
+mod event;
+mod line;
+#[cfg(target_arch = "riscv64")]
+mod pl061;
+mod request;
+
+pub use chip::{GpioBias, GpioChip, GpioDirection, GpioDrive};
+use component::{init_component, ComponentInitError};
+pub use device::{all_devices, get_device, register_chip, GpioDevice};
+pub use event::{LineEvent, LineEventId};
+pub use line::{LineFlags, LineInfo, LineSettings};
+pub use request::LineRequest;
+
+/// The maximum number of the lines in a request.
+pub const LINES_MAX: usize = 64;
+
+/// The errors of GPIO operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioError {
+    /// The operation is not supported by the chip.
+    NotSupported,
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// The line is requested by another consumer.
+    Busy,
+    /// The operation is not allowed in the current configuration of the line.
+    PermissionDenied,
+    /// Other errors reported by the chip.
+    Io,
+}
+
+#[init_component]
+fn gpio_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    pl061::init();
+    ostd::timer::register_callback(event::sample_all);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The configuration and the information of GPIO lines.
+
+use alloc::string::String;
+use core::time::Duration;
+
+use bitflags::bitflags;
+
+use crate::{GpioBias, GpioDirection, GpioDrive, GpioError};
+
+bitflags! {
+    /// The flags of a GPIO line.
+    ///
+    /// The values are the same as Linux's `enum gpio_v2_line_flag`, so they can be exchanged with
+    /// userspace directly.
+    pub struct LineFlags: u64 {
+        /// The line is not available for requests.
+        const USED                  = 1 << 0;
+        /// The line is active low.
+        const ACTIVE_LOW            = 1 << 1;
+        const INPUT                 = 1 << 2;
+        const OUTPUT                = 1 << 3;
+        /// Detects the rising edges of the input line.
+        const EDGE_RISING           = 1 << 4;
+        /// Detects the falling edges of the input line.
+        const EDGE_FALLING          = 1 << 5;
+        const OPEN_DRAIN            = 1 << 6;
+        const OPEN_SOURCE           = 1 << 7;
+        const BIAS_PULL_UP          = 1 << 8;
+        const BIAS_PULL_DOWN        = 1 << 9;
+        const BIAS_DISABLED         = 1 << 10;
+        /// Timestamps the edge events with the real-time clock.
+        const EVENT_CLOCK_REALTIME  = 1 << 11;
+        /// Timestamps the edge events with the hardware timestamp engine.
+        const EVENT_CLOCK_HTE       = 1 << 12;
+
+        const EDGES = Self::EDGE_RISING.bits | Self::EDGE_FALLING.bits;
+        const DRIVES = Self::OPEN_DRAIN.bits | Self::OPEN_SOURCE.bits;
+        const BIASES = Self::BIAS_PULL_UP.bits | Self::BIAS_PULL_DOWN.bits | Self::BIAS_DISABLED.bits;
+    }
+}
+
+/// The settings of a requested GPIO line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineSettings {
+    /// The flags, which do not include [`LineFlags::USED`].
+    ///
+    /// If neither [`LineFlags::INPUT`] nor [`LineFlags::OUTPUT`] is set, the direction of the
+    /// line is left as is.
+    pub flags: LineFlags,
+    /// The logical value of the output line.
+    pub output_value: bool,
+    /// The period for which the input line must be stable before its edges are reported.
+    pub debounce: Duration,
+}
+
+impl LineSettings {
+    /// Checks whether the flags are consistent and supported.
+    pub(crate) fn validate(&self) -> Result<(), GpioError> {
+        let flags = self.flags;
+
+        if flags.contains(LineFlags::USED) {
+            return Err(GpioError::InvalidArgs);
+        }
+        if flags.contains(LineFlags::INPUT | LineFlags::OUTPUT) {
+            return Err(GpioError::InvalidArgs);
+        }
+        // Edge detection requires the input direction.
+        if flags.intersects(LineFlags::EDGES) && !flags.contains(LineFlags::INPUT) {
+            return Err(GpioError::InvalidArgs);
+        }
+        // Drive flags require the output direction, and at most one of them can be set.
+        if flags.intersects(LineFlags::DRIVES)
+            && (!flags.contains(LineFlags::OUTPUT) || flags.contains(LineFlags::DRIVES))
+        {
+            return Err(GpioError::InvalidArgs);
+        }
+        // Bias flags require an explicit direction, and at most one of them can be set.
+        if flags.intersects(LineFlags::BIASES)
+            && (!flags.intersects(LineFlags::INPUT | LineFlags::OUTPUT)
+                || (flags & LineFlags::BIASES).bits().count_ones() > 1)
+        {
+            return Err(GpioError::InvalidArgs);
+        }
+        if flags.contains(LineFlags::EVENT_CLOCK_REALTIME | LineFlags::EVENT_CLOCK_HTE) {
+            return Err(GpioError::InvalidArgs);
+        }
+
+        // TODO: Support timestamping edge events with other clocks.
+        if flags.intersects(LineFlags::EVENT_CLOCK_REALTIME | LineFlags::EVENT_CLOCK_HTE) {
+            return Err(GpioError::NotSupported);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn direction(&self) -> Option<GpioDirection> {
+        if self.flags.contains(LineFlags::INPUT) {
+            Some(GpioDirection::Input)
+        } else if self.flags.contains(LineFlags::OUTPUT) {
+            Some(GpioDirection::Output)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn bias(&self) -> GpioBias {
+        if self.flags.contains(LineFlags::BIAS_PULL_UP) {
+            GpioBias::PullUp
+        } else if self.flags.contains(LineFlags::BIAS_PULL_DOWN) {
+            GpioBias::PullDown
+        } else if self.flags.contains(LineFlags::BIAS_DISABLED) {
+            GpioBias::Disabled
+        } else {
+            GpioBias::AsIs
+        }
+    }
+
+    pub(crate) fn drive(&self) -> GpioDrive {
+        if self.flags.contains(LineFlags::OPEN_DRAIN) {
+            GpioDrive::OpenDrain
+        } else if self.flags.contains(LineFlags::OPEN_SOURCE) {
+            GpioDrive::OpenSource
+        } else {
+            GpioDrive::PushPull
+        }
+    }
+
+    pub(crate) fn is_active_low(&self) -> bool {
+        self.flags.contains(LineFlags::ACTIVE_LOW)
+    }
+}
+
+/// The information of a GPIO line.
+#[derive(Debug, Clone)]
+pub struct LineInfo {
+    /// The name of the line, which is empty if the line is not named.
+    pub name: String,
+    /// The consumer of the line, which is empty if the line is not requested.
+    pub consumer: String,
+    pub offset: u32,
+    pub flags: LineFlags,
+    /// The debounce period of the line, which is zero if the line is not debounced.
+    pub debounce: Duration,
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the ARM PrimeCell GPIO controller (PL061).
+//!
+//! The controller has eight lines. Its interrupts are not used, since the edges are detected by
+//! sampling the lines.
+//!
+//! Reference: ARM PrimeCell General Purpose Input/Output (PL061) Technical Reference Manual.
+
+use alloc::sync::Arc;
+
+use ostd::{
+    arch::device::PL061_IO_MEMS,
+    io::IoMem,
+    mm::VmIoOnce,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::{GpioChip, GpioDirection, GpioError};
+
+pub(super) fn init() {
+    let Some(io_mems) = PL061_IO_MEMS.get() else {
+        return;
+    };
+    for io_mem in io_mems {
+        crate::register_chip(Arc::new(Pl061::new(io_mem.clone())));
+    }
+}
+
+const NGPIO: u32 = 8;
+
+/// The base of the data register.
+///
+/// Bits 9:2 of the address select the bits of the data register that are read or written.
+const GPIODATA: usize = 0x000;
+/// The direction register, in which the bits of the outputs are set.
+const GPIODIR: usize = 0x400;
+/// The interrupt mask register.
+const GPIOIE: usize = 0x410;
+
+#[derive(Debug)]
+struct Pl061 {
+    io_mem: IoMem,
+    /// The lock that serializes the read-modify-write operations on the direction register.
+    dir_lock: SpinLock<(), LocalIrqDisabled>,
+}
+
+impl Pl061 {
+    fn new(io_mem: IoMem) -> Self {
+        // The interrupts are not handled, so they are masked.
+        io_mem.write_once(GPIOIE, &0u32).unwrap();
+
+        Self {
+            io_mem,
+            dir_lock: SpinLock::new(()),
+        }
+    }
+
+    fn data_offset(offset: u32) -> usize {
+        GPIODATA + (1 << (offset + 2))
+    }
+
+    fn update_dir(&self, offset: u32, is_output: bool) {
+        let _guard = self.dir_lock.lock();
+        let dir: u32 = self.io_mem.read_once(GPIODIR).unwrap();
+        let dir = if is_output {
+            dir | (1 << offset)
+        } else {
+            dir & !(1 << offset)
+        };
+        self.io_mem.write_once(GPIODIR, &dir).unwrap();
+    }
+}
+
+impl GpioChip for Pl061 {
+    fn label(&self) -> &str {
+        "pl061"
+    }
+
+    fn ngpio(&self) -> u32 {
+        NGPIO
+    }
+
+    fn direction(&self, offset: u32) -> Result<GpioDirection, GpioError> {
+        let dir: u32 = self.io_mem.read_once(GPIODIR).unwrap();
+        if dir & (1 << offset) != 0 {
+            Ok(GpioDirection::Output)
+        } else {
+            Ok(GpioDirection::Input)
+        }
+    }
+
+    fn direction_input(&self, offset: u32) -> Result<(), GpioError> {
+        self.update_dir(offset, false);
+        Ok(())
+    }
+
+    fn direction_output(&self, offset: u32, value: bool) -> Result<(), GpioError> {
+        // The value is set before and after changing the direction, so that the line does not
+        // glitch, and the value is not lost if the controller ignores writes to inputs.
+        self.set(offset, value)?;
+        self.update_dir(offset, true);
+        self.set(offset, value)
+    }
+
+    fn get(&self, offset: u32) -> Result<bool, GpioError> {
+        let data: u32 = self.io_mem.read_once(Self::data_offset(offset)).unwrap();
+        Ok(data != 0)
+    }
+
+    fn set(&self, offset: u32, value: bool) -> Result<(), GpioError> {
+        // Only the bit selected by the address is written, so no lock is needed.
+        let data: u32 = if value { 1 << offset } else { 0 };
+        self.io_mem
+            .write_once(Self::data_offset(offset), &data)
+            .unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Requests of GPIO lines.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{event::EdgeDetector, GpioDevice, GpioError, LineEvent, LineFlags, LineSettings};
+
+/// A request of some lines of a GPIO device.
+///
+/// The lines in the request are identified by their indexes in the request, e.g., bit `i` of the
+/// values is the value of the `i`-th line. The values are logical values, i.e., they are inverted
+/// for the active-low lines.
+///
+/// The lines are released when the request is dropped.
+#[derive(Debug)]
+pub struct LineRequest {
+    device: Arc<GpioDevice>,
+    offsets: Vec<u32>,
+    edge_detector: Arc<EdgeDetector>,
+}
+
+impl LineRequest {
+    pub(crate) fn new(
+        device: Arc<GpioDevice>,
+        lines: &[(u32, LineSettings)],
+        event_capacity: usize,
+    ) -> Self {
+        let edge_detector = EdgeDetector::new(device.chip().clone(), event_capacity);
+        edge_detector.reset_lines(lines);
+
+        Self {
+            device,
+            offsets: lines.iter().map(|(offset, _)| *offset).collect(),
+            edge_detector,
+        }
+    }
+
+    pub fn device(&self) -> &Arc<GpioDevice> {
+        &self.device
+    }
+
+    /// Returns the offsets of the lines in the request.
+    pub fn offsets(&self) -> &[u32] {
+        &self.offsets
+    }
+
+    /// Returns the values of the lines selected by the mask.
+    pub fn get_values(&self, mask: u64) -> Result<u64, GpioError> {
+        self.check_mask(mask)?;
+
+        let chip = self.device.chip();
+        let mut values = 0;
+        for (i, offset) in self.offsets.iter().enumerate() {
+            if mask & (1 << i) == 0 {
+                continue;
+            }
+            let settings = self.device.settings(*offset);
+            if chip.get(*offset)? ^ settings.is_active_low() {
+                values |= 1 << i;
+            }
+        }
+        Ok(values)
+    }
+
+    /// Sets the values of the lines selected by the mask.
+    ///
+    /// All the selected lines must be outputs.
+    pub fn set_values(&self, values: u64, mask: u64) -> Result<(), GpioError> {
+        self.check_mask(mask)?;
+
+        let selected = self
+            .offsets
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(i, offset)| (i, *offset, self.device.settings(*offset)))
+            .collect::<Vec<_>>();
+        if selected
+            .iter()
+            .any(|(_, _, settings)| !settings.flags.contains(LineFlags::OUTPUT))
+        {
+            return Err(GpioError::PermissionDenied);
+        }
+
+        let chip = self.device.chip();
+        for (i, offset, settings) in selected {
+            let value = (values & (1 << i) != 0) ^ settings.is_active_low();
+            chip.set(offset, value)?;
+        }
+        Ok(())
+    }
+
+    /// Changes the settings of the lines.
+    ///
+    /// The number of the settings must be the same as the number of the lines.
+    pub fn reconfigure(&self, settings: &[LineSettings]) -> Result<(), GpioError> {
+        if settings.len() != self.offsets.len() {
+            return Err(GpioError::InvalidArgs);
+        }
+
+        let lines = self
+            .offsets
+            .iter()
+            .copied()
+            .zip(settings.iter().copied())
+            .collect::<Vec<_>>();
+        self.device.reconfigure(&lines)?;
+        self.edge_detector.reset_lines(&lines);
+        Ok(())
+    }
+
+    /// Takes the oldest pending edge event.
+    pub fn pop_event(&self) -> Option<LineEvent> {
+        self.edge_detector.pop_event()
+    }
+
+    /// Returns whether there are pending edge events.
+    pub fn has_events(&self) -> bool {
+        self.edge_detector.has_events()
+    }
+
+    /// Sets the function to call when new edge events arrive.
+    ///
+    /// The function is called with local IRQs disabled, so it must not sleep.
+    pub fn set_event_notifier(&self, notifier: Box<dyn Fn() + Send + Sync>) {
+        self.edge_detector.set_notifier(notifier);
+    }
+
+    fn check_mask(&self, mask: u64) -> Result<(), GpioError> {
+        if mask == 0 || (self.offsets.len() < 64 && mask >> self.offsets.len() != 0) {
+            return Err(GpioError::InvalidArgs);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LineRequest {
+    fn drop(&mut self) {
+        self.edge_detector.remove();
+        self.device.release(&self.offsets);
+    }
+}
@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! I2C adapters and the transfers performed by them.
+
+use core::fmt::Debug;
+
+use bitflags::bitflags;
+use int_to_c_enum::TryFromInt;
+use ostd::Pod;
+
+use crate::I2cError;
+
+/// An I2C adapter, i.e., the controller of an I2C bus.
+///
+/// An adapter supports plain I2C transfers, SMBus transfers, or both. The supported transfers
+/// are reported by [`I2cAdapter::functionality`].
+pub trait I2cAdapter: Send + Sync + Debug {
+    /// Returns the name of the adapter.
+    fn name(&self) -> &str;
+
+    /// Returns the functionality of the adapter.
+    fn functionality(&self) -> I2cFunc;
+
+    /// Transfers the messages as one I2C transaction.
+    ///
+    /// The messages are separated by repeated START conditions, and the transaction ends with a
+    /// STOP condition. Returns the number of the transferred messages.
+    fn transfer(&self, _msgs: &mut [I2cMsg]) -> Result<usize, I2cError> {
+        Err(I2cError::NotSupported)
+    }
+
+    /// Performs an SMBus transfer.
+    ///
+    /// `command` is ignored by the protocols that do not have a command byte (e.g.,
+    /// [`SmbusProtocol::Quick`]). The data to write are taken from `data`, and the data read are
+    /// stored in `data`.
+    fn smbus_xfer(
+        &self,
+        _addr: u16,
+        _read_write: SmbusReadWrite,
+        _command: u8,
+        _protocol: SmbusProtocol,
+        _data: &mut SmbusData,
+    ) -> Result<(), I2cError> {
+        Err(I2cError::NotSupported)
+    }
+}
+
+/// An I2C message.
+#[derive(Debug)]
+pub struct I2cMsg<'a> {
+    /// The address of the device.
+    pub addr: u16,
+    /// The flags.
+    pub flags: I2cMsgFlags,
+    /// The data to write or the buffer to read into.
+    pub buf: &'a mut [u8],
+}
+
+bitflags! {
+    /// The flags of an I2C message.
+    pub struct I2cMsgFlags: u16 {
+        /// Reads data from the device.
+        const RD            = 0x0001;
+        /// The address is a 10-bit address.
+        const TEN           = 0x0010;
+        /// The length of the message is the first byte received.
+        const RECV_LEN      = 0x0400;
+        /// Skips the acknowledgement of the read bytes.
+        const NO_RD_ACK     = 0x0800;
+        /// Ignores the NAKs from the device.
+        const IGNORE_NAK    = 0x1000;
+        /// Inverts the read/write bit of the address.
+        const REV_DIR_ADDR  = 0x2000;
+        /// Omits the repeated START condition before the message.
+        const NOSTART       = 0x4000;
+        /// Sends a STOP condition after the message.
+        const STOP          = 0x8000;
+    }
+}
+
+bitflags! {
+    /// The functionality of an I2C adapter.
+    ///
+    /// The values are the same as Linux, so they can be returned to userspace directly.
+    pub struct I2cFunc: u32 {
+        /// Plain I2C transfers.
+        const I2C                       = 0x0000_0001;
+        /// 10-bit addresses.
+        const TEN_BIT_ADDR              = 0x0000_0002;
+        /// The mangling flags of messages (e.g., [`I2cMsgFlags::IGNORE_NAK`]).
+        const PROTOCOL_MANGLING         = 0x0000_0004;
+        /// SMBus packet error checking.
+        const SMBUS_PEC                 = 0x0000_0008;
+        /// Messages without repeated START conditions.
+        const NOSTART                   = 0x0000_0010;
+        const SMBUS_BLOCK_PROC_CALL     = 0x0000_8000;
+        const SMBUS_QUICK               = 0x0001_0000;
+        const SMBUS_READ_BYTE           = 0x0002_0000;
+        const SMBUS_WRITE_BYTE          = 0x0004_0000;
+        const SMBUS_READ_BYTE_DATA      = 0x0008_0000;
+        const SMBUS_WRITE_BYTE_DATA     = 0x0010_0000;
+        const SMBUS_READ_WORD_DATA      = 0x0020_0000;
+        const SMBUS_WRITE_WORD_DATA     = 0x0040_0000;
+        const SMBUS_PROC_CALL           = 0x0080_0000;
+        const SMBUS_READ_BLOCK_DATA     = 0x0100_0000;
+        const SMBUS_WRITE_BLOCK_DATA    = 0x0200_0000;
+        const SMBUS_READ_I2C_BLOCK      = 0x0400_0000;
+        const SMBUS_WRITE_I2C_BLOCK     = 0x0800_0000;
+
+        const SMBUS_BYTE = Self::SMBUS_READ_BYTE.bits | Self::SMBUS_WRITE_BYTE.bits;
+        const SMBUS_BYTE_DATA = Self::SMBUS_READ_BYTE_DATA.bits | Self::SMBUS_WRITE_BYTE_DATA.bits;
+        const SMBUS_WORD_DATA = Self::SMBUS_READ_WORD_DATA.bits | Self::SMBUS_WRITE_WORD_DATA.bits;
+        const SMBUS_BLOCK_DATA =
+            Self::SMBUS_READ_BLOCK_DATA.bits | Self::SMBUS_WRITE_BLOCK_DATA.bits;
+        const SMBUS_I2C_BLOCK = Self::SMBUS_READ_I2C_BLOCK.bits | Self::SMBUS_WRITE_I2C_BLOCK.bits;
+    }
+}
+
+/// The direction of an SMBus transfer.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum SmbusReadWrite {
+    Write = 0,
+    Read = 1,
+}
+
+/// The protocol of an SMBus transfer.
+///
+/// The values are the same as the transfer sizes of Linux.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum SmbusProtocol {
+    /// Sends the read/write bit only.
+    Quick = 0,
+    /// Sends or receives a byte without a command.
+    Byte = 1,
+    /// Reads or writes a byte with a command.
+    ByteData = 2,
+    /// Reads or writes a word with a command.
+    WordData = 3,
+    /// Writes a word and reads a word back.
+    ProcCall = 4,
+    /// Reads or writes a block whose length is sent by the sender.
+    BlockData = 5,
+    /// Reads or writes a block whose length is known by both sides.
+    I2cBlockData = 8,
+}
+
+/// The maximum length of an SMBus block.
+pub const SMBUS_BLOCK_MAX: usize = 32;
+
+/// The data of an SMBus transfer.
+///
+/// The layout is the same as Linux's `union i2c_smbus_data`:
+///  - a byte is stored in the first byte;
+///  - a word is stored in the first two bytes in little endian;
+///  - a block is stored with its length in the first byte, followed by the bytes in the block.
+///    One more byte is reserved for the packet error checking.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct SmbusData {
+    bytes: [u8; SMBUS_BLOCK_MAX + 2],
+}
+
+impl SmbusData {
+    /// Creates zeroed data.
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; SMBUS_BLOCK_MAX + 2],
+        }
+    }
+
+    pub fn byte(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn set_byte(&mut self, byte: u8) {
+        self.bytes[0] = byte;
+    }
+
+    pub fn word(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[0], self.bytes[1]])
+    }
+
+    pub fn set_word(&mut self, word: u16) {
+        self.bytes[..2].copy_from_slice(&word.to_le_bytes());
+    }
+
+    /// Returns the block.
+    ///
+    /// This method fails if the length of the block is zero or is larger than
+    /// [`SMBUS_BLOCK_MAX`].
+    pub fn block(&self) -> Result<&[u8], I2cError> {
+        let len = self.bytes[0] as usize;
+        if len == 0 || len > SMBUS_BLOCK_MAX {
+            return Err(I2cError::InvalidArgs);
+        }
+        Ok(&self.bytes[1..=len])
+    }
+
+    /// Sets the block.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the block is longer than [`SMBUS_BLOCK_MAX`].
+    pub fn set_block(&mut self, block: &[u8]) {
+        assert!(block.len() <= SMBUS_BLOCK_MAX);
+        self.bytes[0] = block.len() as u8;
+        self.bytes[1..=block.len()].copy_from_slice(block);
+    }
+}
+
+impl Default for SmbusData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
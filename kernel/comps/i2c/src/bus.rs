@@ -0,0 +1,380 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of I2C adapters, clients, and drivers.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use log::{info, warn};
+use ostd::sync::Mutex;
+
+use crate::{I2cAdapter, I2cClient, I2cError};
+
+/// An I2C driver.
+pub trait I2cDriver: Send + Sync + Debug {
+    /// Returns the names of the clients that the driver can drive.
+    fn id_table(&self) -> &[&str];
+
+    /// Probes a client whose name is in the ID table.
+    ///
+    /// If this method fails, the client can be probed by other drivers.
+    fn probe(&self, client: &Arc<I2cClient>) -> Result<(), I2cError>;
+}
+
+/// The information to instantiate an I2C client.
+#[derive(Debug, Clone)]
+pub struct I2cBoardInfo {
+    /// The name of the client, which is matched against the ID tables of the drivers.
+    pub name: String,
+    /// The 7-bit address of the client.
+    pub addr: u16,
+}
+
+/// Registers an adapter and returns its bus number.
+///
+/// The clients declared for the bus number are instantiated and probed.
+pub fn register_adapter(adapter: Arc<dyn I2cAdapter>) -> u32 {
+    let mut bus = I2C_BUS.lock();
+
+    let bus_nr = bus.next_bus_nr;
+    bus.next_bus_nr += 1;
+    info!("[I2C]: Registered i2c-{}: {}", bus_nr, adapter.name());
+    bus.adapters.insert(bus_nr, adapter);
+
+    let board_infos = bus
+        .board_infos
+        .extract_if(.., |(info_bus_nr, _)| *info_bus_nr == bus_nr)
+        .collect::<Vec<_>>();
+    for (_, info) in board_infos {
+        bus.add_client(bus_nr, info);
+    }
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+
+    bus_nr
+}
+
+/// Returns the adapter of the bus number.
+pub fn get_adapter(bus_nr: u32) -> Option<Arc<dyn I2cAdapter>> {
+    I2C_BUS.lock().adapters.get(&bus_nr).cloned()
+}
+
+/// Returns all the adapters with their bus numbers.
+pub fn all_adapters() -> Vec<(u32, Arc<dyn I2cAdapter>)> {
+    I2C_BUS
+        .lock()
+        .adapters
+        .iter()
+        .map(|(bus_nr, adapter)| (*bus_nr, adapter.clone()))
+        .collect()
+}
+
+/// Declares a client on the bus.
+///
+/// The client is instantiated once the adapter of the bus is registered.
+pub fn register_board_info(bus_nr: u32, info: I2cBoardInfo) {
+    let mut bus = I2C_BUS.lock();
+
+    if !bus.adapters.contains_key(&bus_nr) {
+        bus.board_infos.push((bus_nr, info));
+        return;
+    }
+    bus.add_client(bus_nr, info);
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+}
+
+/// Registers a driver.
+///
+/// The clients that are not driven yet are probed by the driver.
+pub fn register_driver(driver: Arc<dyn I2cDriver>) {
+    let mut bus = I2C_BUS.lock();
+    bus.drivers.push(driver);
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+}
+
+/// Returns whether there is a client that is driven (or being probed) at the address.
+pub fn is_addr_busy(bus_nr: u32, addr: u16) -> bool {
+    I2C_BUS.lock().clients.iter().any(|entry| {
+        entry.client.adapter_nr() == bus_nr
+            && entry.client.addr() == addr
+            && entry.state != ClientState::Unbound
+    })
+}
+
+static I2C_BUS: Mutex<I2cBus> = Mutex::new(I2cBus::new());
+
+struct I2cBus {
+    adapters: BTreeMap<u32, Arc<dyn I2cAdapter>>,
+    next_bus_nr: u32,
+    board_infos: Vec<(u32, I2cBoardInfo)>,
+    clients: Vec<ClientEntry>,
+    drivers: Vec<Arc<dyn I2cDriver>>,
+}
+
+struct ClientEntry {
+    client: Arc<I2cClient>,
+    state: ClientState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClientState {
+    Unbound,
+    Probing,
+    Bound,
+}
+
+/// A client and the drivers to probe it in order.
+type PendingProbe = (Arc<I2cClient>, Vec<Arc<dyn I2cDriver>>);
+
+impl I2cBus {
+    const fn new() -> Self {
+        Self {
+            adapters: BTreeMap::new(),
+            next_bus_nr: 0,
+            board_infos: Vec::new(),
+            clients: Vec::new(),
+            drivers: Vec::new(),
+        }
+    }
+
+    fn add_client(&mut self, bus_nr: u32, info: I2cBoardInfo) {
+        if info.addr > 0x7f {
+            warn!("[I2C]: Invalid address {:#x} for {}", info.addr, info.name);
+            return;
+        }
+        if self
+            .clients
+            .iter()
+            .any(|entry| entry.client.adapter_nr() == bus_nr && entry.client.addr() == info.addr)
+        {
+            warn!(
+                "[I2C]: Address {:#x} on i2c-{} is already used",
+                info.addr, bus_nr
+            );
+            return;
+        }
+
+        let adapter = self.adapters.get(&bus_nr).unwrap().clone();
+        let client = I2cClient::new(bus_nr, adapter, info.name, info.addr);
+        self.clients.push(ClientEntry {
+            client: Arc::new(client),
+            state: ClientState::Unbound,
+        });
+    }
+
+    /// Finds the unbound clients that match some drivers and marks them as being probed.
+    fn take_pending_probes(&mut self) -> Vec<PendingProbe> {
+        let mut probes = Vec::new();
+
+        for entry in self.clients.iter_mut() {
+            if entry.state != ClientState::Unbound {
+                continue;
+            }
+
+            let drivers = self
+                .drivers
+                .iter()
+                .filter(|driver| driver.id_table().contains(&entry.client.name()))
+                .cloned()
+                .collect::<Vec<_>>();
+            if drivers.is_empty() {
+                continue;
+            }
+
+            entry.state = ClientState::Probing;
+            probes.push((entry.client.clone(), drivers));
+        }
+
+        probes
+    }
+
+    fn set_client_state(&mut self, client: &Arc<I2cClient>, state: ClientState) {
+        let entry = self
+            .clients
+            .iter_mut()
+            .find(|entry| Arc::ptr_eq(&entry.client, client))
+            .unwrap();
+        entry.state = state;
+    }
+}
+
+/// Probes the clients without holding the lock, since probing may perform slow transfers.
+fn do_probes(probes: Vec<PendingProbe>) {
+    for (client, drivers) in probes {
+        let is_bound = drivers.iter().any(|driver| match driver.probe(&client) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(
+                    "[I2C]: Failed to probe {} at {:#x} on i2c-{}: {:?}",
+                    client.name(),
+                    client.addr(),
+                    client.adapter_nr(),
+                    err
+                );
+                false
+            }
+        });
+
+        let state = if is_bound {
+            ClientState::Bound
+        } else {
+            ClientState::Unbound
+        };
+        I2C_BUS.lock().set_client_state(&client, state);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::string::ToString;
+
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{I2cFunc, SmbusData, SmbusProtocol, SmbusReadWrite};
+
+    const EEPROM_ADDR: u16 = 0x50;
+
+    /// An SMBus adapter with a single register-based device at [`EEPROM_ADDR`].
+    #[derive(Debug)]
+    struct MockAdapter {
+        regs: Mutex<[u8; 256]>,
+    }
+
+    impl MockAdapter {
+        fn new() -> Self {
+            Self {
+                regs: Mutex::new([0; 256]),
+            }
+        }
+    }
+
+    impl I2cAdapter for MockAdapter {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn functionality(&self) -> I2cFunc {
+            I2cFunc::SMBUS_BYTE_DATA | I2cFunc::SMBUS_WORD_DATA
+        }
+
+        fn smbus_xfer(
+            &self,
+            addr: u16,
+            read_write: SmbusReadWrite,
+            command: u8,
+            protocol: SmbusProtocol,
+            data: &mut SmbusData,
+        ) -> Result<(), I2cError> {
+            if addr != EEPROM_ADDR {
+                return Err(I2cError::NoDevice);
+            }
+
+            let mut regs = self.regs.lock();
+            let reg = command as usize;
+            match (protocol, read_write) {
+                (SmbusProtocol::ByteData, SmbusReadWrite::Read) => data.set_byte(regs[reg]),
+                (SmbusProtocol::ByteData, SmbusReadWrite::Write) => regs[reg] = data.byte(),
+                (SmbusProtocol::WordData, SmbusReadWrite::Read) => {
+                    data.set_word(u16::from_le_bytes([regs[reg], regs[(reg + 1) % 256]]))
+                }
+                (SmbusProtocol::WordData, SmbusReadWrite::Write) => {
+                    let [low, high] = data.word().to_le_bytes();
+                    regs[reg] = low;
+                    regs[(reg + 1) % 256] = high;
+                }
+                _ => return Err(I2cError::NotSupported),
+            }
+            Ok(())
+        }
+    }
+
+    /// A driver that binds the clients that respond to an SMBus read.
+    #[derive(Debug)]
+    struct MockDriver {
+        name: &'static str,
+        clients: Mutex<Vec<Arc<I2cClient>>>,
+    }
+
+    impl MockDriver {
+        fn register(name: &'static str) -> Arc<Self> {
+            let driver = Arc::new(Self {
+                name,
+                clients: Mutex::new(Vec::new()),
+            });
+            register_driver(driver.clone());
+            driver
+        }
+    }
+
+    impl I2cDriver for MockDriver {
+        fn id_table(&self) -> &[&str] {
+            core::slice::from_ref(&self.name)
+        }
+
+        fn probe(&self, client: &Arc<I2cClient>) -> Result<(), I2cError> {
+            client.smbus_read_byte_data(0)?;
+            self.clients.lock().push(client.clone());
+            Ok(())
+        }
+    }
+
+    fn board_info(name: &str, addr: u16) -> I2cBoardInfo {
+        I2cBoardInfo {
+            name: name.to_string(),
+            addr,
+        }
+    }
+
+    #[ktest]
+    fn register_and_transfer() {
+        let driver = MockDriver::register("mock-eeprom");
+        let bus_nr = register_adapter(Arc::new(MockAdapter::new()));
+        assert_eq!(get_adapter(bus_nr).unwrap().name(), "mock");
+
+        register_board_info(bus_nr, board_info("mock-eeprom", EEPROM_ADDR));
+        assert!(is_addr_busy(bus_nr, EEPROM_ADDR));
+        let client = driver.clients.lock().pop().unwrap();
+        assert_eq!(client.adapter_nr(), bus_nr);
+        assert_eq!(client.addr(), EEPROM_ADDR);
+
+        client.smbus_write_byte_data(0x10, 0xab).unwrap();
+        assert_eq!(client.smbus_read_byte_data(0x10), Ok(0xab));
+        client.smbus_write_word_data(0x20, 0x1234).unwrap();
+        assert_eq!(client.smbus_read_byte_data(0x20), Ok(0x34));
+        assert_eq!(client.smbus_read_word_data(0x20), Ok(0x1234));
+    }
+
+    #[ktest]
+    fn error_paths() {
+        let driver = MockDriver::register("mock-probe");
+        let bus_nr = register_adapter(Arc::new(MockAdapter::new()));
+
+        // A client without a device fails to be probed and stays unbound.
+        register_board_info(bus_nr, board_info("mock-probe", EEPROM_ADDR + 1));
+        assert!(!is_addr_busy(bus_nr, EEPROM_ADDR + 1));
+        assert!(driver.clients.lock().is_empty());
+
+        // Clients at invalid or used addresses are not instantiated.
+        register_board_info(bus_nr, board_info("mock-probe", 0x80));
+        assert!(!is_addr_busy(bus_nr, 0x80));
+        register_board_info(bus_nr, board_info("mock-probe", EEPROM_ADDR));
+        register_board_info(bus_nr, board_info("mock-probe", EEPROM_ADDR));
+        assert!(is_addr_busy(bus_nr, EEPROM_ADDR));
+        assert_eq!(driver.clients.lock().len(), 1);
+
+        // The transfers that the adapter does not support fail.
+        let client = driver.clients.lock().pop().unwrap();
+        assert_eq!(client.read(&mut [0; 4]), Err(I2cError::NotSupported));
+        assert_eq!(client.smbus_read_byte(), Err(I2cError::NotSupported));
+        assert_eq!(SmbusData::new().block(), Err(I2cError::InvalidArgs));
+    }
+}
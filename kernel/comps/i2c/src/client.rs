@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! I2C clients.
+
+use alloc::{string::String, sync::Arc};
+
+use crate::{I2cAdapter, I2cError, I2cMsg, I2cMsgFlags, SmbusData, SmbusProtocol, SmbusReadWrite};
+
+/// An I2C client, i.e., a device at some address on an adapter.
+#[derive(Debug)]
+pub struct I2cClient {
+    adapter_nr: u32,
+    adapter: Arc<dyn I2cAdapter>,
+    name: String,
+    addr: u16,
+}
+
+impl I2cClient {
+    pub(crate) fn new(
+        adapter_nr: u32,
+        adapter: Arc<dyn I2cAdapter>,
+        name: String,
+        addr: u16,
+    ) -> Self {
+        Self {
+            adapter_nr,
+            adapter,
+            name,
+            addr,
+        }
+    }
+
+    /// Returns the bus number of the adapter.
+    pub fn adapter_nr(&self) -> u32 {
+        self.adapter_nr
+    }
+
+    pub fn adapter(&self) -> &Arc<dyn I2cAdapter> {
+        &self.adapter
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    /// Reads bytes from the client in a single message.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), I2cError> {
+        let mut msgs = [I2cMsg {
+            addr: self.addr,
+            flags: I2cMsgFlags::RD,
+            buf,
+        }];
+        self.adapter.transfer(&mut msgs).map(|_| ())
+    }
+
+    /// Writes bytes to the client in a single message.
+    pub fn write(&self, buf: &[u8]) -> Result<(), I2cError> {
+        let mut buf = buf.to_vec();
+        let mut msgs = [I2cMsg {
+            addr: self.addr,
+            flags: I2cMsgFlags::empty(),
+            buf: &mut buf,
+        }];
+        self.adapter.transfer(&mut msgs).map(|_| ())
+    }
+
+    pub fn smbus_read_byte(&self) -> Result<u8, I2cError> {
+        let mut data = SmbusData::new();
+        self.smbus_xfer(SmbusReadWrite::Read, 0, SmbusProtocol::Byte, &mut data)?;
+        Ok(data.byte())
+    }
+
+    pub fn smbus_write_byte(&self, value: u8) -> Result<(), I2cError> {
+        // The byte is sent as the command byte.
+        let mut data = SmbusData::new();
+        self.smbus_xfer(SmbusReadWrite::Write, value, SmbusProtocol::Byte, &mut data)
+    }
+
+    pub fn smbus_read_byte_data(&self, command: u8) -> Result<u8, I2cError> {
+        let mut data = SmbusData::new();
+        self.smbus_xfer(
+            SmbusReadWrite::Read,
+            command,
+            SmbusProtocol::ByteData,
+            &mut data,
+        )?;
+        Ok(data.byte())
+    }
+
+    pub fn smbus_write_byte_data(&self, command: u8, value: u8) -> Result<(), I2cError> {
+        let mut data = SmbusData::new();
+        data.set_byte(value);
+        self.smbus_xfer(
+            SmbusReadWrite::Write,
+            command,
+            SmbusProtocol::ByteData,
+            &mut data,
+        )
+    }
+
+    pub fn smbus_read_word_data(&self, command: u8) -> Result<u16, I2cError> {
+        let mut data = SmbusData::new();
+        self.smbus_xfer(
+            SmbusReadWrite::Read,
+            command,
+            SmbusProtocol::WordData,
+            &mut data,
+        )?;
+        Ok(data.word())
+    }
+
+    pub fn smbus_write_word_data(&self, command: u8, value: u16) -> Result<(), I2cError> {
+        let mut data = SmbusData::new();
+        data.set_word(value);
+        self.smbus_xfer(
+            SmbusReadWrite::Write,
+            command,
+            SmbusProtocol::WordData,
+            &mut data,
+        )
+    }
+
+    fn smbus_xfer(
+        &self,
+        read_write: SmbusReadWrite,
+        command: u8,
+        protocol: SmbusProtocol,
+        data: &mut SmbusData,
+    ) -> Result<(), I2cError> {
+        self.adapter
+            .smbus_xfer(self.addr, read_write, command, protocol, data)
+    }
+}
@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the SMBus controllers in the Intel 82801 (ICH) family and the later PCHs.
+//!
+//! The controller only performs SMBus transfers, so plain I2C transfers are not supported. The
+//! transfers are completed by polling the status register.
+//!
+//! Reference: Intel I/O Controller Hub 9 (ICH9) Family Datasheet, Section 12.
+
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use bitflags::bitflags;
+use log::warn;
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            cfg_space::{Bar, Command, IoBar},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    sync::Mutex,
+    task::Task,
+    timer::Jiffies,
+};
+
+use crate::{
+    bus, I2cAdapter, I2cError, I2cFunc, SmbusData, SmbusProtocol, SmbusReadWrite, SMBUS_BLOCK_MAX,
+};
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(I801Driver));
+}
+
+const INTEL_VENDOR_ID: u16 = 0x8086;
+const SERIAL_BUS_CLASS: u8 = 0x0c;
+const SMBUS_SUBCLASS: u8 = 0x05;
+
+/// The index of the BAR that contains the SMBus registers.
+const SMB_BAR_INDEX: u8 = 4;
+/// The number of the SMBus registers in bytes.
+const SMB_REGS_SIZE: u32 = 16;
+
+/// The host configuration register in the PCI configuration space.
+const SMBHSTCFG: u16 = 0x40;
+const SMBHSTCFG_HST_EN: u8 = 1 << 0;
+
+// The offsets of the SMBus registers.
+const SMBHSTSTS: u32 = 0;
+const SMBHSTCNT: u32 = 2;
+const SMBHSTCMD: u32 = 3;
+const SMBHSTADD: u32 = 4;
+const SMBHSTDAT0: u32 = 5;
+const SMBHSTDAT1: u32 = 6;
+const SMBBLKDAT: u32 = 7;
+const SMBAUXCTL: u32 = 13;
+
+bitflags! {
+    /// The host status register.
+    struct HostStatus: u8 {
+        const HOST_BUSY     = 1 << 0;
+        const INTR          = 1 << 1;
+        const DEV_ERR       = 1 << 2;
+        const BUS_ERR       = 1 << 3;
+        const FAILED        = 1 << 4;
+        const SMBALERT      = 1 << 5;
+        const INUSE_STS     = 1 << 6;
+        const BYTE_DONE     = 1 << 7;
+
+        const ERRORS = Self::DEV_ERR.bits | Self::BUS_ERR.bits | Self::FAILED.bits;
+        /// The bits that are cleared before and after a transaction.
+        const FLAGS = Self::INTR.bits | Self::ERRORS.bits | Self::BYTE_DONE.bits;
+    }
+}
+
+bitflags! {
+    /// The host control register.
+    struct HostControl: u8 {
+        const KILL          = 1 << 1;
+        const START         = 1 << 6;
+    }
+}
+
+/// The SMB command field of the host control register.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
+enum HostCommand {
+    Quick = 0x00,
+    Byte = 0x04,
+    ByteData = 0x08,
+    WordData = 0x0c,
+    ProcCall = 0x10,
+    BlockData = 0x14,
+}
+
+/// The 32-byte buffer enable bit of the auxiliary control register.
+const SMBAUXCTL_E32B: u8 = 1 << 1;
+
+/// The maximum time to wait for a transaction.
+const TRANSACTION_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+struct I801Driver;
+
+impl PciDriver for I801Driver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.vendor_id != INTEL_VENDOR_ID
+            || device_id.class != SERIAL_BUS_CLASS
+            || device_id.subclass != SMBUS_SUBCLASS
+        {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        let Some(Bar::Io(io_bar)) = device.bar_manager().bar(SMB_BAR_INDEX).clone() else {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        };
+        if io_bar.size() < SMB_REGS_SIZE {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        }
+
+        // The SMBus host controller should have been enabled by the firmware. We do not enable
+        // it ourselves, since the firmware may disable it on purpose.
+        if device.read_device_config8(SMBHSTCFG) & SMBHSTCFG_HST_EN == 0 {
+            warn!("[I2C]: The i801 SMBus host controller is disabled by the firmware");
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        }
+        device.set_command(device.command() | Command::IO_SPACE);
+
+        let adapter = Arc::new(I801Adapter {
+            device_id,
+            regs: Mutex::new(I801Regs { io_bar }),
+        });
+        bus::register_adapter(adapter.clone());
+
+        Ok(adapter)
+    }
+}
+
+#[derive(Debug)]
+struct I801Adapter {
+    device_id: PciDeviceId,
+    /// The registers, which are locked during a transfer.
+    regs: Mutex<I801Regs>,
+}
+
+impl PciDevice for I801Adapter {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
+
+impl I2cAdapter for I801Adapter {
+    fn name(&self) -> &str {
+        "SMBus I801 adapter"
+    }
+
+    fn functionality(&self) -> I2cFunc {
+        I2cFunc::SMBUS_QUICK
+            | I2cFunc::SMBUS_BYTE
+            | I2cFunc::SMBUS_BYTE_DATA
+            | I2cFunc::SMBUS_WORD_DATA
+            | I2cFunc::SMBUS_PROC_CALL
+            | I2cFunc::SMBUS_BLOCK_DATA
+    }
+
+    fn smbus_xfer(
+        &self,
+        addr: u16,
+        read_write: SmbusReadWrite,
+        command: u8,
+        protocol: SmbusProtocol,
+        data: &mut SmbusData,
+    ) -> Result<(), I2cError> {
+        if addr > 0x7f {
+            return Err(I2cError::InvalidArgs);
+        }
+        let is_read = read_write == SmbusReadWrite::Read;
+
+        let regs = self.regs.lock();
+
+        // The process call writes before it reads, so the address is sent with the write bit.
+        let addr_byte = if is_read && protocol != SmbusProtocol::ProcCall {
+            ((addr as u8) << 1) | 1
+        } else {
+            (addr as u8) << 1
+        };
+        regs.write(SMBHSTADD, addr_byte);
+
+        match protocol {
+            SmbusProtocol::Quick => regs.transaction(HostCommand::Quick),
+            SmbusProtocol::Byte => {
+                if !is_read {
+                    regs.write(SMBHSTCMD, command);
+                }
+                regs.transaction(HostCommand::Byte)?;
+                if is_read {
+                    data.set_byte(regs.read(SMBHSTDAT0));
+                }
+                Ok(())
+            }
+            SmbusProtocol::ByteData => {
+                regs.write(SMBHSTCMD, command);
+                if !is_read {
+                    regs.write(SMBHSTDAT0, data.byte());
+                }
+                regs.transaction(HostCommand::ByteData)?;
+                if is_read {
+                    data.set_byte(regs.read(SMBHSTDAT0));
+                }
+                Ok(())
+            }
+            SmbusProtocol::WordData | SmbusProtocol::ProcCall => {
+                regs.write(SMBHSTCMD, command);
+                if !is_read || protocol == SmbusProtocol::ProcCall {
+                    let [low, high] = data.word().to_le_bytes();
+                    regs.write(SMBHSTDAT0, low);
+                    regs.write(SMBHSTDAT1, high);
+                }
+                let host_command = if protocol == SmbusProtocol::ProcCall {
+                    HostCommand::ProcCall
+                } else {
+                    HostCommand::WordData
+                };
+                regs.transaction(host_command)?;
+                if is_read || protocol == SmbusProtocol::ProcCall {
+                    let low = regs.read(SMBHSTDAT0);
+                    let high = regs.read(SMBHSTDAT1);
+                    data.set_word(u16::from_le_bytes([low, high]));
+                }
+                Ok(())
+            }
+            SmbusProtocol::BlockData => {
+                regs.write(SMBHSTCMD, command);
+                regs.block_transaction(is_read, data)
+            }
+            SmbusProtocol::I2cBlockData => Err(I2cError::NotSupported),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct I801Regs {
+    io_bar: Arc<IoBar>,
+}
+
+impl I801Regs {
+    fn read(&self, offset: u32) -> u8 {
+        // The offset is within the BAR, whose size has been checked during probing.
+        self.io_bar.read(offset).unwrap()
+    }
+
+    fn write(&self, offset: u32, value: u8) {
+        self.io_bar.write(offset, value).unwrap()
+    }
+
+    fn status(&self) -> HostStatus {
+        HostStatus::from_bits_truncate(self.read(SMBHSTSTS))
+    }
+
+    /// Transfers a block with the 32-byte buffer.
+    fn block_transaction(&self, is_read: bool, data: &mut SmbusData) -> Result<(), I2cError> {
+        let aux_control = self.read(SMBAUXCTL);
+        self.write(SMBAUXCTL, aux_control | SMBAUXCTL_E32B);
+
+        let result = self.do_block_transaction(is_read, data);
+
+        self.write(SMBAUXCTL, aux_control & !SMBAUXCTL_E32B);
+        result
+    }
+
+    fn do_block_transaction(&self, is_read: bool, data: &mut SmbusData) -> Result<(), I2cError> {
+        // Reading the host control register resets the index of the 32-byte buffer.
+        self.read(SMBHSTCNT);
+
+        if !is_read {
+            let block = data.block()?;
+            self.write(SMBHSTDAT0, block.len() as u8);
+            for byte in block {
+                self.write(SMBBLKDAT, *byte);
+            }
+        }
+
+        self.transaction(HostCommand::BlockData)?;
+
+        if is_read {
+            let len = self.read(SMBHSTDAT0) as usize;
+            if len == 0 || len > SMBUS_BLOCK_MAX {
+                return Err(I2cError::Protocol);
+            }
+            let mut block = [0u8; SMBUS_BLOCK_MAX];
+            for byte in block[..len].iter_mut() {
+                *byte = self.read(SMBBLKDAT);
+            }
+            data.set_block(&block[..len]);
+        }
+
+        Ok(())
+    }
+
+    /// Starts a transaction and waits for its completion.
+    fn transaction(&self, command: HostCommand) -> Result<(), I2cError> {
+        let status = self.status();
+        if status.contains(HostStatus::HOST_BUSY) {
+            return Err(I2cError::Busy);
+        }
+        // The status bits are cleared by writing ones.
+        self.write(SMBHSTSTS, (status & HostStatus::FLAGS).bits());
+
+        self.write(SMBHSTCNT, HostControl::START.bits() | command as u8);
+
+        let result = self.wait_for_completion();
+        let status = self.status();
+        self.write(SMBHSTSTS, (status & HostStatus::FLAGS).bits());
+        result?;
+
+        if status.contains(HostStatus::DEV_ERR) {
+            Err(I2cError::NoDevice)
+        } else if status.contains(HostStatus::BUS_ERR) {
+            Err(I2cError::ArbitrationLost)
+        } else if status.contains(HostStatus::FAILED) {
+            Err(I2cError::Io)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn wait_for_completion(&self) -> Result<(), I2cError> {
+        let deadline = Jiffies::elapsed().as_duration() + TRANSACTION_TIMEOUT;
+
+        loop {
+            let status = self.status();
+            if !status.contains(HostStatus::HOST_BUSY)
+                && status.intersects(HostStatus::INTR | HostStatus::ERRORS)
+            {
+                return Ok(());
+            }
+
+            if Jiffies::elapsed().as_duration() >= deadline {
+                break;
+            }
+            Task::yield_now();
+        }
+
+        // Kill the transaction to make the controller idle again.
+        warn!("[I2C]: The i801 SMBus transaction timed out");
+        self.write(SMBHSTCNT, HostControl::KILL.bits());
+        Task::yield_now();
+        self.write(SMBHSTCNT, 0);
+
+        Err(I2cError::Timeout)
+    }
+}
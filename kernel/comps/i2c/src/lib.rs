@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I2C subsystem of Asterinas.
+//!
+//! The subsystem follows the model of Linux:
+//!  - An adapter ([`I2cAdapter`]) is the controller of an I2C bus. Each adapter is identified by
+//!    its bus number, which is assigned when the adapter is registered.
+//!  - A client ([`I2cClient`]) is a device at some address on an adapter. Clients are declared by
+//!    [`register_board_info`], since I2C devices cannot be enumerated in general.
+//!  - A driver ([`I2cDriver`]) drives the clients whose names are in its ID table.
+//!
+//! Userspace can also access the adapters directly via `/dev/i2c-N`, which is implemented by the
+//! kernel on top of [`get_adapter`].
+#![no_std]
+#![deny(unsafe_code)]
+#![feature(extract_if)]
+
+extern crate alloc;
+
+mod adapter;
+mod bus;
+mod client;
+#[cfg(target_arch = "x86_64")]
+mod i801;
+
+pub use adapter::{
+    I2cAdapter, I2cFunc, I2cMsg, I2cMsgFlags, SmbusData, SmbusProtocol, SmbusReadWrite,
+    SMBUS_BLOCK_MAX,
+};
+pub use bus::{
+    all_adapters, get_adapter, is_addr_busy, register_adapter, register_board_info,
+    register_driver, I2cBoardInfo, I2cDriver,
+};
+pub use client::I2cClient;
+use component::{init_component, ComponentInitError};
+
+/// The errors of I2C transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cError {
+    /// The operation is not supported by the adapter.
+    NotSupported,
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// No device acknowledges the address.
+    NoDevice,
+    /// The adapter is busy.
+    Busy,
+    /// The arbitration of the bus is lost.
+    ArbitrationLost,
+    /// The device responds with data that violates the protocol.
+    Protocol,
+    /// The transfer is not completed in time.
+    Timeout,
+    /// Other errors reported by the adapter.
+    Io,
+}
+
+#[init_component]
+fn i2c_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "x86_64")]
+    i801::init();
+    Ok(())
+}
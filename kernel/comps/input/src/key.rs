@@ -119,6 +119,14 @@ pub enum Key {
     Delete = 111,
 
     LeftMeta = 125,
+    RightMeta = 126,
+
+    /// The left button of a mouse.
+    BtnLeft = 0x110,
+    /// The right button of a mouse.
+    BtnRight = 0x111,
+    /// The middle button of a mouse.
+    BtnMiddle = 0x112,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! The input devices of Asterinas.
+//!
+//! The drivers register the input devices, which report the events through the callbacks. The
+//! events and the capabilities of the devices follow the event codes of Linux, so they can be
+//! passed to userspace through the evdev interface directly.
 #![no_std]
 #![deny(unsafe_code)]
 #![feature(fn_traits)]
@@ -13,6 +17,7 @@ use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::{any::Any, fmt::Debug};
 
 use component::{init_component, ComponentInitError};
+use int_to_c_enum::TryFromInt;
 use key::{Key, KeyStatus};
 use ostd::sync::SpinLock;
 use spin::Once;
@@ -20,10 +25,113 @@ use spin::Once;
 #[derive(Debug, Clone, Copy)]
 pub enum InputEvent {
     KeyBoard(Key, KeyStatus),
+    /// A relative motion along the axis (e.g., a mouse movement).
+    Relative(RelAxis, i32),
+    /// The end of a group of events that happen at the same time.
+    ///
+    /// For example, the horizontal and the vertical motions of a mouse are followed by one
+    /// `Sync` event.
+    Sync,
+}
+
+/// The axes of the relative motions.
+///
+/// The values are the same as the `REL_*` codes of Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u16)]
+pub enum RelAxis {
+    X = 0x00,
+    Y = 0x01,
+    Wheel = 0x08,
+}
+
+/// The number of the key codes, which is the same as `KEY_CNT` of Linux.
+pub const KEY_COUNT: usize = 0x300;
+/// The number of the relative axes, which is the same as `REL_CNT` of Linux.
+pub const REL_COUNT: usize = 0x10;
+
+/// The keys and the axes that an input device can report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputCapability {
+    keys: [u64; KEY_COUNT / 64],
+    rel_axes: u64,
+}
+
+impl InputCapability {
+    /// Creates a capability without any keys or axes.
+    pub const fn new() -> Self {
+        Self {
+            keys: [0; KEY_COUNT / 64],
+            rel_axes: 0,
+        }
+    }
+
+    /// Creates the capability of a keyboard, which has all the keyboard keys.
+    pub fn keyboard() -> Self {
+        let mut capability = Self::new();
+        (0..Key::BtnLeft as u16)
+            .filter_map(|code| Key::try_from(code).ok())
+            .filter(|key| *key != Key::Reserved)
+            .for_each(|key| capability.add_key(key));
+        capability
+    }
+
+    /// Creates the capability of a mouse with three buttons and a wheel.
+    pub fn mouse() -> Self {
+        let mut capability = Self::new();
+        for key in [Key::BtnLeft, Key::BtnRight, Key::BtnMiddle] {
+            capability.add_key(key);
+        }
+        for axis in [RelAxis::X, RelAxis::Y, RelAxis::Wheel] {
+            capability.add_rel_axis(axis);
+        }
+        capability
+    }
+
+    pub fn add_key(&mut self, key: Key) {
+        let code = key as usize;
+        self.keys[code / 64] |= 1 << (code % 64);
+    }
+
+    pub fn add_rel_axis(&mut self, axis: RelAxis) {
+        self.rel_axes |= 1 << (axis as u16);
+    }
+
+    pub fn has_key(&self, key: Key) -> bool {
+        let code = key as usize;
+        self.keys[code / 64] & (1 << (code % 64)) != 0
+    }
+
+    pub fn has_keys(&self) -> bool {
+        self.keys.iter().any(|bits| *bits != 0)
+    }
+
+    pub fn has_rel_axes(&self) -> bool {
+        self.rel_axes != 0
+    }
+
+    /// Returns the bitmap of the key codes.
+    pub fn key_bitmap(&self) -> &[u64] {
+        &self.keys
+    }
+
+    /// Returns the bitmap of the relative axes.
+    pub fn rel_axis_bitmap(&self) -> u64 {
+        self.rel_axes
+    }
+}
+
+impl Default for InputCapability {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub trait InputDevice: Send + Sync + Any + Debug {
     fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync));
+
+    /// Returns the keys and the axes that the device can report.
+    fn capability(&self) -> InputCapability;
 }
 
 pub fn register_device(name: String, device: Arc<dyn InputDevice>) {
@@ -76,8 +76,8 @@ impl<D: BlockSet + 'static> aster_block::BlockDevice for MlsDisk<D> {
     ) -> core::result::Result<(), aster_block::bio::BioEnqueueError> {
         use aster_block::bio::{BioStatus, BioType, SubmittedBio};
 
-        if bio.type_() == BioType::Discard {
-            warn!("discard operation not supported");
+        if bio.type_() == BioType::Discard || bio.type_() == BioType::WriteZeroes {
+            warn!("{:?} operation not supported", bio.type_());
             bio.complete(BioStatus::NotSupported);
             return Ok(());
         }
@@ -1,14 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::linked_list::LinkedList, sync::Arc};
+use alloc::sync::Arc;
 
-use aster_softirq::BottomHalfDisabled;
 use ostd::{
     mm::{
         Daddr, DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, Infallible, VmReader,
         VmWriter, PAGE_SIZE,
     },
-    sync::SpinLock,
+    sync::RingBuffer,
     Pod,
 };
 use spin::Once;
@@ -18,21 +17,17 @@ use crate::dma_pool::{DmaPool, DmaSegment};
 pub struct TxBuffer {
     dma_stream: DmaStream,
     nbytes: usize,
-    pool: &'static SpinLock<LinkedList<DmaStream>, BottomHalfDisabled>,
+    pool: &'static RingBuffer<DmaStream>,
 }
 
 impl TxBuffer {
-    pub fn new<H: Pod>(
-        header: &H,
-        packet: &[u8],
-        pool: &'static SpinLock<LinkedList<DmaStream>, BottomHalfDisabled>,
-    ) -> Self {
+    pub fn new<H: Pod>(header: &H, packet: &[u8], pool: &'static RingBuffer<DmaStream>) -> Self {
         let header = header.as_bytes();
         let nbytes = header.len() + packet.len();
 
         assert!(nbytes <= TX_BUFFER_LEN);
 
-        let dma_stream = if let Some(stream) = pool.lock().pop_front() {
+        let dma_stream = if let Some(stream) = pool.pop() {
             stream
         } else {
             let segment = FrameAllocOptions::new()
@@ -79,7 +74,8 @@ impl HasDaddr for TxBuffer {
 
 impl Drop for TxBuffer {
     fn drop(&mut self) {
-        self.pool.lock().push_back(self.dma_stream.clone());
+        // If the pool is full, the DMA stream is unmapped and freed.
+        let _ = self.pool.push(self.dma_stream.clone());
     }
 }
 
@@ -140,6 +136,8 @@ impl HasDaddr for RxBuffer {
 
 pub const RX_BUFFER_LEN: usize = 4096;
 pub const TX_BUFFER_LEN: usize = 4096;
+/// The maximum number of the free DMA streams kept in a pool of [`TxBuffer`]s.
+pub const TX_BUFFER_POOL_SIZE: usize = 256;
 pub static RX_BUFFER_POOL: Once<Arc<DmaPool>> = Once::new();
 
 pub fn init() {
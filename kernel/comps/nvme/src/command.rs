@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The commands and the completions of NVMe.
+//!
+//! Reference: NVM Express Base Specification, Revision 2.0, Section 4.2 (Submission Queue Entry)
+//! and Section 4.6 (Completion Queue Entry).
+
+use core::mem::size_of;
+
+use ostd::Pod;
+
+/// The size of a submission queue entry.
+pub(crate) const COMMAND_SIZE: usize = size_of::<NvmeCommand>();
+/// The size of a completion queue entry.
+pub(crate) const COMPLETION_SIZE: usize = size_of::<NvmeCompletion>();
+
+/// The opcodes of the admin commands.
+pub(crate) mod admin_opcode {
+    pub(crate) const CREATE_IO_SQ: u8 = 0x01;
+    pub(crate) const CREATE_IO_CQ: u8 = 0x05;
+    pub(crate) const IDENTIFY: u8 = 0x06;
+    pub(crate) const SET_FEATURES: u8 = 0x09;
+}
+
+/// The opcodes of the I/O commands of the NVM command set.
+pub(crate) mod io_opcode {
+    pub(crate) const FLUSH: u8 = 0x00;
+    pub(crate) const WRITE: u8 = 0x01;
+    pub(crate) const READ: u8 = 0x02;
+}
+
+/// The values of the Controller or Namespace Structure (CNS) field of the Identify command.
+pub(crate) mod identify_cns {
+    /// The Identify Namespace data structure of the namespace.
+    pub(crate) const NAMESPACE: u32 = 0x00;
+    /// The Identify Controller data structure of the controller.
+    pub(crate) const CONTROLLER: u32 = 0x01;
+    /// The list of the active namespace IDs.
+    pub(crate) const ACTIVE_NAMESPACES: u32 = 0x02;
+}
+
+/// The feature ID of the Number of Queues feature.
+pub(crate) const FEATURE_NUMBER_OF_QUEUES: u32 = 0x07;
+
+/// A submission queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod)]
+pub(crate) struct NvmeCommand {
+    pub(crate) opcode: u8,
+    pub(crate) flags: u8,
+    pub(crate) cid: u16,
+    pub(crate) nsid: u32,
+    pub(crate) cdw2: u32,
+    pub(crate) cdw3: u32,
+    pub(crate) mptr: u64,
+    pub(crate) prp1: u64,
+    pub(crate) prp2: u64,
+    pub(crate) cdw10: u32,
+    pub(crate) cdw11: u32,
+    pub(crate) cdw12: u32,
+    pub(crate) cdw13: u32,
+    pub(crate) cdw14: u32,
+    pub(crate) cdw15: u32,
+}
+
+impl NvmeCommand {
+    pub(crate) fn identify(cns: u32, nsid: u32, prp1: u64) -> Self {
+        Self {
+            opcode: admin_opcode::IDENTIFY,
+            nsid,
+            prp1,
+            cdw10: cns,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn set_features(fid: u32, value: u32) -> Self {
+        Self {
+            opcode: admin_opcode::SET_FEATURES,
+            cdw10: fid,
+            cdw11: value,
+            ..Default::default()
+        }
+    }
+
+    /// Creates an I/O completion queue, whose completions are signaled by the interrupt vector
+    /// if it is not `None`.
+    pub(crate) fn create_io_cq(qid: u16, size: u16, prp1: u64, vector: Option<u16>) -> Self {
+        const PHYSICALLY_CONTIGUOUS: u32 = 1 << 0;
+        const INTERRUPTS_ENABLED: u32 = 1 << 1;
+
+        let cdw11 = match vector {
+            Some(vector) => ((vector as u32) << 16) | INTERRUPTS_ENABLED | PHYSICALLY_CONTIGUOUS,
+            None => PHYSICALLY_CONTIGUOUS,
+        };
+        Self {
+            opcode: admin_opcode::CREATE_IO_CQ,
+            prp1,
+            cdw10: ((size as u32 - 1) << 16) | qid as u32,
+            cdw11,
+            ..Default::default()
+        }
+    }
+
+    /// Creates an I/O submission queue, whose completions are posted to the completion queue.
+    pub(crate) fn create_io_sq(qid: u16, size: u16, prp1: u64, cqid: u16) -> Self {
+        const PHYSICALLY_CONTIGUOUS: u32 = 1 << 0;
+
+        Self {
+            opcode: admin_opcode::CREATE_IO_SQ,
+            prp1,
+            cdw10: ((size as u32 - 1) << 16) | qid as u32,
+            cdw11: ((cqid as u32) << 16) | PHYSICALLY_CONTIGUOUS,
+            ..Default::default()
+        }
+    }
+
+    /// Reads or writes `nr_blocks` logical blocks starting from `lba`.
+    pub(crate) fn read_write(
+        opcode: u8,
+        nsid: u32,
+        lba: u64,
+        nr_blocks: u16,
+        prp1: u64,
+        prp2: u64,
+    ) -> Self {
+        Self {
+            opcode,
+            nsid,
+            prp1,
+            prp2,
+            cdw10: lba as u32,
+            cdw11: (lba >> 32) as u32,
+            // The number of the logical blocks is zero-based.
+            cdw12: (nr_blocks - 1) as u32,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn flush(nsid: u32) -> Self {
+        Self {
+            opcode: io_opcode::FLUSH,
+            nsid,
+            ..Default::default()
+        }
+    }
+}
+
+/// A completion queue entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod)]
+pub(crate) struct NvmeCompletion {
+    /// The command specific result.
+    pub(crate) result: u32,
+    pub(crate) reserved: u32,
+    pub(crate) sq_head: u16,
+    pub(crate) sq_id: u16,
+    pub(crate) cid: u16,
+    /// The phase tag (bit 0) and the status (bits 1 to 15).
+    pub(crate) status: u16,
+}
+
+impl NvmeCompletion {
+    /// The offset of the `status` field, which contains the phase tag.
+    pub(crate) const STATUS_OFFSET: usize = 14;
+
+    /// Returns the status, which is zero if the command succeeds.
+    pub(crate) fn status_code(&self) -> u16 {
+        self.status >> 1
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::mem::offset_of;
+
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn layout() {
+        assert_eq!(COMMAND_SIZE, 64);
+        assert_eq!(COMPLETION_SIZE, 16);
+        assert_eq!(
+            NvmeCompletion::STATUS_OFFSET,
+            offset_of!(NvmeCompletion, status)
+        );
+        assert_eq!(offset_of!(NvmeCommand, prp1), 24);
+        assert_eq!(offset_of!(NvmeCommand, cdw10), 40);
+    }
+
+    #[ktest]
+    fn parse_completion() {
+        let mut bytes = [0u8; COMPLETION_SIZE];
+        bytes[0..4].copy_from_slice(&0x0003_0007u32.to_le_bytes());
+        bytes[8..10].copy_from_slice(&5u16.to_le_bytes());
+        bytes[12..14].copy_from_slice(&42u16.to_le_bytes());
+        // The status is "Invalid Field in Command" (0x2) with the phase tag set.
+        bytes[14..16].copy_from_slice(&((0x2u16 << 1) | 1).to_le_bytes());
+
+        let completion = NvmeCompletion::from_bytes(&bytes);
+        assert_eq!(completion.result, 0x0003_0007);
+        assert_eq!(completion.sq_head, 5);
+        assert_eq!(completion.cid, 42);
+        assert_eq!(completion.status_code(), 0x2);
+
+        bytes[14..16].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(NvmeCompletion::from_bytes(&bytes).status_code(), 0);
+    }
+
+    #[ktest]
+    fn build_commands() {
+        let command = NvmeCommand::create_io_cq(1, 128, 0x1000, Some(3));
+        assert_eq!(command.cdw10, (127 << 16) | 1);
+        assert_eq!(command.cdw11, (3 << 16) | 0b11);
+        let command = NvmeCommand::create_io_cq(2, 64, 0x1000, None);
+        assert_eq!(command.cdw10, (63 << 16) | 2);
+        assert_eq!(command.cdw11, 0b01);
+
+        let command = NvmeCommand::create_io_sq(2, 64, 0x2000, 2);
+        assert_eq!(command.cdw10, (63 << 16) | 2);
+        assert_eq!(command.cdw11, (2 << 16) | 0b01);
+
+        let command = NvmeCommand::read_write(io_opcode::READ, 1, 0x1_2345_6789, 8, 0x3000, 0);
+        assert_eq!(command.cdw10, 0x2345_6789);
+        assert_eq!(command.cdw11, 0x1);
+        assert_eq!(command.cdw12, 7);
+    }
+}
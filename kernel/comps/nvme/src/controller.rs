@@ -0,0 +1,511 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The NVMe controllers.
+//!
+//! The controller is initialized as follows:
+//!  1. The controller is disabled, and the admin queue pair is registered.
+//!  2. The controller is enabled, and the admin commands are submitted to identify the controller
+//!     and to negotiate the number of the I/O queue pairs.
+//!  3. An I/O queue pair is created for each CPU (if the controller allows), and each of them has
+//!     its own MSI-X interrupt vector.
+//!
+//! The admin commands are only submitted during the initialization, so their completions are
+//! polled instead of being signaled by interrupts.
+//!
+//! Reference: NVM Express Base Specification, Revision 2.0, Section 3.1 (Register Definition) and
+//! Section 3.5 (Controller Initialization).
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{hint::spin_loop, mem::size_of, time::Duration};
+
+use log::{info, warn};
+use ostd::{
+    bus::pci::{
+        capability::{msix::CapabilityMsixData, CapabilityData},
+        cfg_space::{Bar, Command},
+        common_device::PciCommonDevice,
+        PciDeviceLocation,
+    },
+    cpu::{current_cpu_racy, CpuSet},
+    io::IoMem,
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+    sync::SpinLock,
+    timer::Jiffies,
+    trap::IrqLine,
+};
+
+use crate::{
+    command::{identify_cns, NvmeCommand, NvmeCompletion, FEATURE_NUMBER_OF_QUEUES},
+    namespace::{complete_io, IoContext},
+    queue::QueuePair,
+    NvmeError,
+};
+
+// The offsets of the registers.
+const REG_CAP: usize = 0x00;
+const REG_VS: usize = 0x08;
+const REG_CC: usize = 0x14;
+const REG_CSTS: usize = 0x1c;
+const REG_AQA: usize = 0x24;
+const REG_ASQ: usize = 0x28;
+const REG_ACQ: usize = 0x30;
+
+const CC_ENABLE: u32 = 1 << 0;
+/// The size of the submission queue entries, which is 2^6 = 64 bytes.
+const CC_IOSQES: u32 = 6 << 16;
+/// The size of the completion queue entries, which is 2^4 = 16 bytes.
+const CC_IOCQES: u32 = 4 << 20;
+
+const CSTS_READY: u32 = 1 << 0;
+const CSTS_FATAL: u32 = 1 << 1;
+
+/// The controller supports the NVM command set.
+const CAP_CSS_NVM: u64 = 1 << 37;
+
+const ADMIN_QUEUE_SIZE: u16 = 32;
+const IO_QUEUE_SIZE: u16 = 128;
+/// The maximum number of the I/O queue pairs.
+const MAX_NR_IO_QUEUES: usize = 64;
+
+/// The maximum time to wait for an admin command.
+const ADMIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum size of the data transferred by one command, which is limited by the size of a
+/// PRP list (i.e., one page).
+const MAX_PRP_TRANSFER_SIZE: usize = (PAGE_SIZE / size_of::<u64>()) * PAGE_SIZE;
+
+/// An NVMe controller.
+pub(crate) struct NvmeController {
+    index: usize,
+    /// The location of the PCI device, which the DMA mappings are made for.
+    location: PciDeviceLocation,
+    io_mem: IoMem,
+    admin_queue: QueuePair<()>,
+    io_queues: Vec<Arc<QueuePair<IoContext>>>,
+    /// The MSI-X capability, which owns the IRQ lines of the I/O queues.
+    ///
+    /// The I/O queues are polled by the timer if the controller does not support MSI-X.
+    #[expect(dead_code)]
+    msix: SpinLock<Option<CapabilityMsixData>>,
+    max_transfer_size: usize,
+    has_volatile_write_cache: bool,
+}
+
+/// The fields of the Identify Controller data structure that are used by the driver.
+struct ControllerInfo {
+    max_transfer_size: usize,
+    has_volatile_write_cache: bool,
+}
+
+impl NvmeController {
+    /// Initializes the controller.
+    pub(crate) fn init(index: usize, device: &PciCommonDevice) -> Result<Arc<Self>, NvmeError> {
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(0).clone() else {
+            return Err(NvmeError::NotSupported);
+        };
+        device.set_command(device.command() | Command::MEMORY_SPACE | Command::BUS_MASTER);
+        let io_mem = bar.io_mem().clone();
+
+        let cap: u64 = io_mem.read_once(REG_CAP).unwrap();
+        let max_queue_size = (cap & 0xffff) as u16 + 1;
+        let ready_timeout = Duration::from_millis(((cap >> 24) & 0xff) * 500);
+        let doorbell_stride = 4 << ((cap >> 32) & 0xf);
+        // The memory page size of the controller must be the page size of the host, which is
+        // 2^(12 + MPSMIN) bytes at least.
+        if cap & CAP_CSS_NVM == 0 || (cap >> 48) & 0xf != 0 {
+            return Err(NvmeError::NotSupported);
+        }
+
+        Self::disable(&io_mem, ready_timeout)?;
+
+        let admin_queue = QueuePair::new(
+            0,
+            ADMIN_QUEUE_SIZE.min(max_queue_size),
+            io_mem.clone(),
+            doorbell_stride,
+            *device.location(),
+        )?;
+        let admin_queue_sizes = (admin_queue.size() as u32 - 1) * 0x1_0001;
+        io_mem.write_once(REG_AQA, &admin_queue_sizes).unwrap();
+        write_u64(&io_mem, REG_ASQ, admin_queue.sq_daddr());
+        write_u64(&io_mem, REG_ACQ, admin_queue.cq_daddr());
+
+        io_mem
+            .write_once(REG_CC, &(CC_ENABLE | CC_IOSQES | CC_IOCQES))
+            .unwrap();
+        wait_for_ready(&io_mem, true, ready_timeout)?;
+
+        let version: u32 = io_mem.read_once(REG_VS).unwrap();
+        let mut controller = Self {
+            index,
+            location: *device.location(),
+            io_mem,
+            admin_queue,
+            io_queues: Vec::new(),
+            msix: SpinLock::new(None),
+            max_transfer_size: 0,
+            has_volatile_write_cache: false,
+        };
+
+        let info = controller.identify_controller(version)?;
+        controller.max_transfer_size = info.max_transfer_size;
+        controller.has_volatile_write_cache = info.has_volatile_write_cache;
+
+        let mut msix = device
+            .capabilities()
+            .iter()
+            .find_map(|cap| match cap.capability_data() {
+                CapabilityData::Msix(msix) => Some(msix.clone()),
+                _ => None,
+            })
+            // The admin queue uses the first vector, which is kept masked. The I/O queues are
+            // polled if there are no other vectors.
+            .filter(|msix| msix.table_size() > 1);
+        let max_nr_io_queues = msix
+            .as_ref()
+            .map_or(1, |msix| msix.table_size() as usize - 1);
+        let nr_io_queues = controller.set_nr_io_queues(
+            ostd::cpu::num_cpus()
+                .min(max_nr_io_queues)
+                .min(MAX_NR_IO_QUEUES),
+        )?;
+
+        for qid in 1..=nr_io_queues as u16 {
+            let queue = Arc::new(QueuePair::new(
+                qid,
+                IO_QUEUE_SIZE.min(max_queue_size),
+                controller.io_mem.clone(),
+                doorbell_stride,
+                controller.location,
+            )?);
+
+            let vector = msix.as_ref().map(|_| qid);
+            controller.admin_command(NvmeCommand::create_io_cq(
+                qid,
+                queue.size(),
+                queue.cq_daddr(),
+                vector,
+            ))?;
+            controller.admin_command(NvmeCommand::create_io_sq(
+                qid,
+                queue.size(),
+                queue.sq_daddr(),
+                qid,
+            ))?;
+
+            if let Some(msix) = msix.as_mut() {
+                let irq = IrqLine::alloc().map_err(|_| NvmeError::NoMemory)?;
+                msix.set_interrupt_vector(irq, qid);
+                // The interrupts are handled by the CPUs that submit to the queue.
+                let mut cpus = CpuSet::new_empty();
+                for cpu in ostd::cpu::all_cpus()
+                    .filter(|cpu| cpu.as_usize() % nr_io_queues == qid as usize - 1)
+                {
+                    cpus.add(cpu);
+                }
+                msix.set_affinity_hint(qid, &cpus);
+                let cloned_queue = queue.clone();
+                msix.irq_mut(qid as usize).unwrap().on_active(move |_| {
+                    cloned_queue.poll(complete_io);
+                });
+            }
+
+            controller.io_queues.push(queue);
+        }
+
+        let is_polled = msix.is_none();
+        controller.msix = SpinLock::new(msix);
+        let controller = Arc::new(controller);
+
+        if is_polled {
+            let cloned_controller = controller.clone();
+            ostd::timer::register_callback(move || {
+                for queue in cloned_controller.io_queues.iter() {
+                    queue.poll(complete_io);
+                }
+            });
+        }
+
+        Ok(controller)
+    }
+
+    /// Returns the index of the controller, which is `X` in the name `nvmeX`.
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the location of the PCI device.
+    pub(crate) fn location(&self) -> PciDeviceLocation {
+        self.location
+    }
+
+    /// Returns the I/O queue pair of the current CPU.
+    pub(crate) fn io_queue(&self) -> &QueuePair<IoContext> {
+        let cpu = current_cpu_racy().as_usize();
+        &self.io_queues[cpu % self.io_queues.len()]
+    }
+
+    /// Returns the maximum size of the data transferred by one I/O command.
+    pub(crate) fn max_transfer_size(&self) -> usize {
+        self.max_transfer_size
+    }
+
+    /// Returns whether the controller has a volatile write cache, which needs to be flushed.
+    pub(crate) fn has_volatile_write_cache(&self) -> bool {
+        self.has_volatile_write_cache
+    }
+
+    /// Returns the IDs of the active namespaces.
+    pub(crate) fn active_namespaces(&self) -> Result<Vec<u32>, NvmeError> {
+        let buf = alloc_identify_buf(self.location)?;
+        self.admin_command(NvmeCommand::identify(
+            identify_cns::ACTIVE_NAMESPACES,
+            0,
+            buf.daddr() as u64,
+        ))?;
+
+        let mut nsids = Vec::new();
+        for offset in (0..PAGE_SIZE).step_by(size_of::<u32>()) {
+            let nsid: u32 = buf.read_val(offset).unwrap();
+            if nsid == 0 {
+                break;
+            }
+            nsids.push(nsid);
+        }
+        Ok(nsids)
+    }
+
+    /// Returns the size of the logical blocks and the number of the logical blocks of the
+    /// namespace.
+    pub(crate) fn identify_namespace(&self, nsid: u32) -> Result<(usize, u64), NvmeError> {
+        let buf = alloc_identify_buf(self.location)?;
+        self.admin_command(NvmeCommand::identify(
+            identify_cns::NAMESPACE,
+            nsid,
+            buf.daddr() as u64,
+        ))?;
+
+        let mut data = [0u8; IDENTIFY_NAMESPACE_LEN];
+        buf.read_bytes(0, &mut data).unwrap();
+        parse_identify_namespace(&data)
+    }
+
+    fn identify_controller(&self, version: u32) -> Result<ControllerInfo, NvmeError> {
+        const OFFSET_SN: usize = 4;
+        const OFFSET_MN: usize = 24;
+        const OFFSET_MDTS: usize = 77;
+        const OFFSET_VWC: usize = 525;
+
+        let buf = alloc_identify_buf(self.location)?;
+        self.admin_command(NvmeCommand::identify(
+            identify_cns::CONTROLLER,
+            0,
+            buf.daddr() as u64,
+        ))?;
+
+        let mut serial = [0u8; 20];
+        buf.read_bytes(OFFSET_SN, &mut serial).unwrap();
+        let mut model = [0u8; 40];
+        buf.read_bytes(OFFSET_MN, &mut model).unwrap();
+        info!(
+            "[NVMe]: nvme{}: {} (serial {}), version {}.{}",
+            self.index,
+            core::str::from_utf8(&model).unwrap_or("unknown").trim(),
+            core::str::from_utf8(&serial).unwrap_or("unknown").trim(),
+            version >> 16,
+            (version >> 8) & 0xff,
+        );
+
+        let max_transfer_size = max_transfer_size_of(buf.read_val(OFFSET_MDTS).unwrap());
+        let has_volatile_write_cache = buf.read_val::<u8>(OFFSET_VWC).unwrap() & 1 != 0;
+
+        Ok(ControllerInfo {
+            max_transfer_size,
+            has_volatile_write_cache,
+        })
+    }
+
+    /// Requests the number of the I/O queue pairs, and returns the number of the I/O queue
+    /// pairs allocated by the controller.
+    fn set_nr_io_queues(&self, nr_queues: usize) -> Result<usize, NvmeError> {
+        let value = (nr_queues as u32 - 1) * 0x1_0001;
+        let completion =
+            self.admin_command(NvmeCommand::set_features(FEATURE_NUMBER_OF_QUEUES, value))?;
+
+        let nr_sqs = (completion.result & 0xffff) as usize + 1;
+        let nr_cqs = (completion.result >> 16) as usize + 1;
+        Ok(nr_queues.min(nr_sqs).min(nr_cqs))
+    }
+
+    /// Submits the admin command and polls for its completion.
+    fn admin_command(&self, command: NvmeCommand) -> Result<NvmeCompletion, NvmeError> {
+        self.admin_queue.submit(command, ());
+
+        let deadline = Jiffies::elapsed().as_duration() + ADMIN_TIMEOUT;
+        loop {
+            let mut result = None;
+            self.admin_queue
+                .poll(|completion, _| result = Some(completion));
+
+            match result {
+                Some(completion) if completion.status_code() == 0 => return Ok(completion),
+                Some(completion) => {
+                    warn!(
+                        "[NVMe]: nvme{}: The admin command {:#x} failed with status {:#x}",
+                        self.index,
+                        command.opcode,
+                        completion.status_code()
+                    );
+                    return Err(NvmeError::CommandFailed(completion.status_code()));
+                }
+                None if Jiffies::elapsed().as_duration() >= deadline => {
+                    warn!(
+                        "[NVMe]: nvme{}: The admin command {:#x} timed out",
+                        self.index, command.opcode
+                    );
+                    return Err(NvmeError::Timeout);
+                }
+                None => spin_loop(),
+            }
+        }
+    }
+
+    fn disable(io_mem: &IoMem, ready_timeout: Duration) -> Result<(), NvmeError> {
+        let config: u32 = io_mem.read_once(REG_CC).unwrap();
+        if config & CC_ENABLE != 0 {
+            io_mem.write_once(REG_CC, &(config & !CC_ENABLE)).unwrap();
+        }
+        wait_for_ready(io_mem, false, ready_timeout)
+    }
+}
+
+/// Waits for the controller to become ready (i.e., enabled) or not ready (i.e., disabled).
+fn wait_for_ready(io_mem: &IoMem, is_ready: bool, timeout: Duration) -> Result<(), NvmeError> {
+    let deadline = Jiffies::elapsed().as_duration() + timeout;
+
+    loop {
+        let status: u32 = io_mem.read_once(REG_CSTS).unwrap();
+        if status & CSTS_FATAL != 0 {
+            return Err(NvmeError::ControllerFatal);
+        }
+        if (status & CSTS_READY != 0) == is_ready {
+            return Ok(());
+        }
+        if Jiffies::elapsed().as_duration() >= deadline {
+            return Err(NvmeError::Timeout);
+        }
+        spin_loop();
+    }
+}
+
+/// Writes a 64-bit register as two 32-bit halves, since some controllers do not support 64-bit
+/// accesses.
+fn write_u64(io_mem: &IoMem, offset: usize, value: u64) {
+    io_mem.write_once(offset, &(value as u32)).unwrap();
+    io_mem
+        .write_once(offset + 4, &((value >> 32) as u32))
+        .unwrap();
+}
+
+/// The length of the leading part of the Identify Namespace data structure, which ends with the
+/// LBA formats.
+const IDENTIFY_NAMESPACE_LEN: usize = 192;
+
+/// Parses the size of the logical blocks and the number of the logical blocks from the Identify
+/// Namespace data structure.
+fn parse_identify_namespace(
+    data: &[u8; IDENTIFY_NAMESPACE_LEN],
+) -> Result<(usize, u64), NvmeError> {
+    const OFFSET_NSZE: usize = 0;
+    const OFFSET_FLBAS: usize = 26;
+    const OFFSET_LBAF: usize = 128;
+
+    let nr_blocks = u64::from_le_bytes(data[OFFSET_NSZE..OFFSET_NSZE + 8].try_into().unwrap());
+    let format_index = (data[OFFSET_FLBAS] & 0xf) as usize;
+    let format_offset = OFFSET_LBAF + format_index * size_of::<u32>();
+    let format = u32::from_le_bytes(
+        data[format_offset..format_offset + size_of::<u32>()]
+            .try_into()
+            .unwrap(),
+    );
+    let block_size_bits = (format >> 16) & 0xff;
+    if !(9..=PAGE_SIZE.ilog2()).contains(&block_size_bits) {
+        return Err(NvmeError::NotSupported);
+    }
+
+    Ok((1 << block_size_bits, nr_blocks))
+}
+
+/// Converts the Maximum Data Transfer Size (MDTS) field to the size in bytes.
+///
+/// The field is in units of the minimum memory page size, which is the page size of the host.
+/// Zero means no limit.
+fn max_transfer_size_of(mdts: u8) -> usize {
+    match PAGE_SIZE.checked_shl(mdts as u32) {
+        Some(size) if mdts != 0 => size.min(MAX_PRP_TRANSFER_SIZE),
+        _ => MAX_PRP_TRANSFER_SIZE,
+    }
+}
+
+fn alloc_identify_buf(location: PciDeviceLocation) -> Result<DmaCoherent, NvmeError> {
+    let segment = FrameAllocOptions::new()
+        .alloc_segment(1)
+        .map_err(|_| NvmeError::NoMemory)?;
+    DmaCoherent::map_for_device(segment.into(), true, location).map_err(|_| NvmeError::NoMemory)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// Builds an Identify Namespace data structure whose LBA format `index` is in use.
+    fn identify_namespace_data(
+        nr_blocks: u64,
+        index: u8,
+        block_size_bits: u32,
+    ) -> [u8; IDENTIFY_NAMESPACE_LEN] {
+        let mut data = [0u8; IDENTIFY_NAMESPACE_LEN];
+        data[0..8].copy_from_slice(&nr_blocks.to_le_bytes());
+        // The upper bits of FLBAS are not part of the index.
+        data[26] = 0x10 | index;
+        // Other LBA formats are filled with an unsupported block size.
+        for i in 0..16 {
+            data[128 + i * 4..132 + i * 4].copy_from_slice(&(3u32 << 16).to_le_bytes());
+        }
+        let offset = 128 + index as usize * 4;
+        data[offset..offset + 4].copy_from_slice(&(block_size_bits << 16).to_le_bytes());
+        data
+    }
+
+    #[ktest]
+    fn parse_namespace() {
+        let data = identify_namespace_data(0x1_0000_0001, 0, 9);
+        assert_eq!(parse_identify_namespace(&data), Ok((512, 0x1_0000_0001)));
+
+        let data = identify_namespace_data(1024, 15, 12);
+        assert_eq!(parse_identify_namespace(&data), Ok((4096, 1024)));
+
+        // The block size must be between 512 bytes and the page size.
+        let data = identify_namespace_data(1024, 2, 8);
+        assert_eq!(
+            parse_identify_namespace(&data),
+            Err(NvmeError::NotSupported)
+        );
+        let data = identify_namespace_data(1024, 2, PAGE_SIZE.ilog2() + 1);
+        assert_eq!(
+            parse_identify_namespace(&data),
+            Err(NvmeError::NotSupported)
+        );
+    }
+
+    #[ktest]
+    fn max_transfer_size() {
+        assert_eq!(max_transfer_size_of(0), MAX_PRP_TRANSFER_SIZE);
+        assert_eq!(max_transfer_size_of(1), PAGE_SIZE * 2);
+        assert_eq!(max_transfer_size_of(5), PAGE_SIZE * 32);
+        assert_eq!(max_transfer_size_of(20), MAX_PRP_TRANSFER_SIZE);
+        assert_eq!(max_transfer_size_of(u8::MAX), MAX_PRP_TRANSFER_SIZE);
+    }
+}
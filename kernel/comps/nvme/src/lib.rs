@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The NVMe driver of Asterinas.
+//!
+//! The driver supports the NVMe controllers attached to PCI Express. Each controller has an admin
+//! queue pair and an I/O queue pair for each CPU, and each active namespace of the controller is
+//! registered to the block layer as a block device named `nvmeXnY`, where `X` is the index of the
+//! controller and `Y` is the namespace ID.
+//!
+//! Only the NVM command set and the read, write, and flush commands are supported.
+//!
+//! Reference: <https://nvmexpress.org/specifications/>
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod command;
+mod controller;
+mod namespace;
+mod pci;
+mod queue;
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+pub use namespace::NvmeNamespace;
+use ostd::sync::SpinLock;
+
+/// The errors of NVMe controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NvmeError {
+    /// The controller or the namespace is not supported by the driver.
+    NotSupported,
+    /// The memory for the queues or the buffers cannot be allocated.
+    NoMemory,
+    /// The controller does not respond in time.
+    Timeout,
+    /// The controller reports a fatal error.
+    ControllerFatal,
+    /// The command fails with the status.
+    CommandFailed(u16),
+}
+
+static NAMESPACES: SpinLock<Vec<Arc<NvmeNamespace>>> = SpinLock::new(Vec::new());
+
+/// Returns all the NVMe namespaces.
+pub fn all_namespaces() -> Vec<Arc<NvmeNamespace>> {
+    NAMESPACES.lock().clone()
+}
+
+fn register_namespace(namespace: Arc<NvmeNamespace>) {
+    aster_block::register_device(namespace.name().to_string(), namespace.clone());
+    NAMESPACES.lock().push(namespace);
+}
+
+#[init_component]
+fn nvme_init() -> Result<(), ComponentInitError> {
+    pci::init();
+    aster_block::bio::bio_segment_pool_init();
+    Ok(())
+}
@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The NVMe namespaces, which are the block devices of the controllers.
+//!
+//! The bios are submitted directly to the I/O queue pair of the current CPU, so there is no
+//! software staging queue and no thread to dispatch the requests. Each segment of a bio is
+//! transferred by one or more commands, and the bio is completed when all of them are completed.
+//!
+//! The data buffer of a command is described by the Physical Region Page (PRP) entries. The first
+//! entry may start at any offset in a page, while the other entries must be page aligned. If more
+//! than two entries are needed, the second entry points to a PRP list, which is a page of entries.
+//!
+//! Reference: NVM Express Base Specification, Revision 2.0, Section 4.1.1 (Physical Region Page
+//! Entry and List).
+
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+use core::{
+    fmt::Debug,
+    mem::size_of,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    BlockDeviceMeta, SECTOR_SIZE,
+};
+use log::warn;
+use ostd::{
+    bus::pci::PciDeviceLocation,
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, PAGE_SIZE},
+};
+
+use crate::{
+    command::{io_opcode, NvmeCommand, NvmeCompletion},
+    controller::NvmeController,
+};
+
+/// An NVMe namespace.
+pub struct NvmeNamespace {
+    name: String,
+    nsid: u32,
+    controller: Arc<NvmeController>,
+    block_size: usize,
+    nr_blocks: u64,
+}
+
+impl NvmeNamespace {
+    pub(crate) fn new(controller: Arc<NvmeController>, nsid: u32) -> Option<Self> {
+        let (block_size, nr_blocks) = match controller.identify_namespace(nsid) {
+            Ok(info) => info,
+            Err(err) => {
+                warn!(
+                    "[NVMe]: nvme{}: Failed to identify the namespace {}: {:?}",
+                    controller.index(),
+                    nsid,
+                    err
+                );
+                return None;
+            }
+        };
+
+        Some(Self {
+            name: format!("nvme{}n{}", controller.index(), nsid),
+            nsid,
+            controller,
+            block_size,
+            nr_blocks,
+        })
+    }
+
+    /// Returns the name of the namespace (e.g., `nvme0n1`).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the logical blocks in bytes.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Builds the commands that transfer the data of the bio.
+    ///
+    /// Returns `None` if the bio is not aligned to the logical blocks.
+    fn build_commands(
+        &self,
+        bio: &SubmittedBio,
+    ) -> Option<Vec<(NvmeCommand, Option<DmaCoherent>)>> {
+        let opcode = match bio.type_() {
+            BioType::Read => io_opcode::READ,
+            BioType::Write => io_opcode::WRITE,
+            _ => unreachable!(),
+        };
+
+        let start = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+        if start % self.block_size != 0 {
+            return None;
+        }
+        let mut lba = (start / self.block_size) as u64;
+
+        let mut commands = Vec::new();
+        for segment in bio.segments() {
+            let dma_slice = segment.inner_dma_slice();
+            let daddr = dma_slice.daddr();
+            let len = dma_slice.nbytes();
+            if len % self.block_size != 0 {
+                return None;
+            }
+
+            let mut offset = 0;
+            while offset < len {
+                let addr = daddr + offset;
+                let max_len = self.controller.max_transfer_size() - addr % PAGE_SIZE;
+                let chunk_len = (len - offset).min(max_len) / self.block_size * self.block_size;
+                if chunk_len == 0 {
+                    return None;
+                }
+
+                let (prp2, prp_list) = build_prp2(addr, chunk_len, self.controller.location())?;
+                let nr_blocks = (chunk_len / self.block_size) as u16;
+                let command =
+                    NvmeCommand::read_write(opcode, self.nsid, lba, nr_blocks, addr as u64, prp2);
+                commands.push((command, prp_list));
+
+                offset += chunk_len;
+                lba += nr_blocks as u64;
+            }
+        }
+
+        Some(commands)
+    }
+}
+
+impl aster_block::BlockDevice for NvmeNamespace {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let commands = match bio.type_() {
+            BioType::Read | BioType::Write => {
+                let Some(commands) = self.build_commands(&bio) else {
+                    bio.complete(BioStatus::IoError);
+                    return Ok(());
+                };
+                commands
+            }
+            BioType::Flush if self.controller.has_volatile_write_cache() => {
+                vec![(NvmeCommand::flush(self.nsid), None)]
+            }
+            // The data are already persistent without a volatile write cache.
+            BioType::Flush => {
+                bio.complete(BioStatus::Complete);
+                return Ok(());
+            }
+            BioType::Discard | BioType::WriteZeroes => {
+                bio.complete(BioStatus::NotSupported);
+                return Ok(());
+            }
+        };
+        if commands.is_empty() {
+            bio.complete(BioStatus::Complete);
+            return Ok(());
+        }
+
+        let request = Arc::new(IoRequest {
+            bio,
+            nr_remaining: AtomicUsize::new(commands.len()),
+            is_failed: AtomicBool::new(false),
+        });
+        let queue = self.controller.io_queue();
+        for (command, prp_list) in commands {
+            let context = IoContext {
+                request: request.clone(),
+                _prp_list: prp_list,
+            };
+            queue.submit(command, context);
+        }
+
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            // The segments are transferred by separate commands, so they are not limited by the
+            // controller.
+            max_nr_segments_per_bio: usize::MAX,
+            nr_sectors: (self.nr_blocks as usize * self.block_size) / SECTOR_SIZE,
+        }
+    }
+}
+
+impl Debug for NvmeNamespace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NvmeNamespace")
+            .field("name", &self.name)
+            .field("nsid", &self.nsid)
+            .field("block_size", &self.block_size)
+            .field("nr_blocks", &self.nr_blocks)
+            .finish()
+    }
+}
+
+/// The context of an I/O command.
+pub(crate) struct IoContext {
+    request: Arc<IoRequest>,
+    /// The PRP list, which must live until the command is completed.
+    _prp_list: Option<DmaCoherent>,
+}
+
+/// A bio that is being transferred by some I/O commands.
+struct IoRequest {
+    bio: SubmittedBio,
+    /// The number of the commands that are not completed.
+    nr_remaining: AtomicUsize,
+    is_failed: AtomicBool,
+}
+
+/// Completes an I/O command, and completes its bio if it is the last command of the bio.
+pub(crate) fn complete_io(completion: NvmeCompletion, context: IoContext) {
+    let request = context.request;
+
+    if completion.status_code() != 0 {
+        warn!(
+            "[NVMe]: The I/O command failed with status {:#x}",
+            completion.status_code()
+        );
+        request.is_failed.store(true, Ordering::Relaxed);
+    }
+    if request.nr_remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+        return;
+    }
+
+    if request.is_failed.load(Ordering::Relaxed) {
+        request.bio.complete(BioStatus::IoError);
+        return;
+    }
+    // Synchronize DMA mapping if read from the device
+    if request.bio.type_() == BioType::Read {
+        request
+            .bio
+            .segments()
+            .iter()
+            .for_each(|segment| segment.inner_dma_slice().sync().unwrap());
+    }
+    request.bio.complete(BioStatus::Complete);
+}
+
+/// Builds the second PRP entry of the data buffer, which is either the second page or a PRP
+/// list of the other pages.
+///
+/// The first PRP entry is the start address of the data buffer. Returns `None` if the PRP list
+/// cannot be allocated.
+fn build_prp2(
+    addr: usize,
+    len: usize,
+    location: PciDeviceLocation,
+) -> Option<(u64, Option<DmaCoherent>)> {
+    let first_len = PAGE_SIZE - addr % PAGE_SIZE;
+    if len <= first_len {
+        return Some((0, None));
+    }
+
+    let second_page = addr + first_len;
+    let nr_other_pages = (len - first_len).div_ceil(PAGE_SIZE);
+    if nr_other_pages == 1 {
+        return Some((second_page as u64, None));
+    }
+
+    debug_assert!(nr_other_pages <= PAGE_SIZE / size_of::<u64>());
+    let segment = FrameAllocOptions::new().alloc_segment(1).ok()?;
+    let prp_list = DmaCoherent::map_for_device(segment.into(), true, location).ok()?;
+    for i in 0..nr_other_pages {
+        let entry = (second_page + i * PAGE_SIZE) as u64;
+        prp_list.write_val(i * size_of::<u64>(), &entry).unwrap();
+    }
+    Some((prp_list.daddr() as u64, Some(prp_list)))
+}
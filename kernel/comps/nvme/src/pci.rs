@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI driver of the NVMe controllers.
+//!
+//! The controllers are only claimed when the PCI bus is probed, and they are initialized after
+//! that, since the initialization waits for the controllers and registers the block devices.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use log::warn;
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    sync::SpinLock,
+};
+
+use crate::{controller::NvmeController, namespace::NvmeNamespace, register_namespace};
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(NvmePciDriver));
+
+    let devices = core::mem::take(&mut *CLAIMED_DEVICES.lock());
+    for (index, device) in devices.into_iter().enumerate() {
+        init_controller(index, device);
+    }
+}
+
+const MASS_STORAGE_CLASS: u8 = 0x01;
+const NVM_SUBCLASS: u8 = 0x08;
+const PROG_IF_NVME: u8 = 0x02;
+
+/// The controllers that are claimed but not initialized.
+static CLAIMED_DEVICES: SpinLock<Vec<PciCommonDevice>> = SpinLock::new(Vec::new());
+
+fn init_controller(index: usize, device: PciCommonDevice) {
+    let controller = match NvmeController::init(index, &device) {
+        Ok(controller) => controller,
+        Err(err) => {
+            warn!("[NVMe]: Failed to initialize nvme{}: {:?}", index, err);
+            return;
+        }
+    };
+
+    let nsids = match controller.active_namespaces() {
+        Ok(nsids) => nsids,
+        Err(err) => {
+            warn!(
+                "[NVMe]: nvme{}: Failed to list the namespaces: {:?}",
+                index, err
+            );
+            return;
+        }
+    };
+    for nsid in nsids {
+        if let Some(namespace) = NvmeNamespace::new(controller.clone(), nsid) {
+            register_namespace(Arc::new(namespace));
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NvmePciDriver;
+
+impl PciDriver for NvmePciDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.class != MASS_STORAGE_CLASS
+            || device_id.subclass != NVM_SUBCLASS
+            || device_id.prog_if != PROG_IF_NVME
+        {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        CLAIMED_DEVICES.lock().push(device);
+        Ok(Arc::new(NvmePciDevice { device_id }))
+    }
+}
+
+#[derive(Debug)]
+struct NvmePciDevice {
+    device_id: PciDeviceId,
+}
+
+impl PciDevice for NvmePciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
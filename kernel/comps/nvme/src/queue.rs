@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The queue pairs of NVMe.
+//!
+//! A queue pair consists of a submission queue (SQ) and a completion queue (CQ), both of which are
+//! circular buffers in the memory shared with the controller. The host writes the commands to the
+//! SQ and rings the SQ tail doorbell, and the controller posts the completions to the CQ. The
+//! host recognizes the new completions by their phase tags, which are inverted each time the
+//! controller wraps around the CQ, and rings the CQ head doorbell after consuming them.
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::sync::atomic::{fence, Ordering};
+
+use id_alloc::IdAlloc;
+use log::warn;
+use ostd::{
+    bus::pci::PciDeviceLocation,
+    io::IoMem,
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+    sync::SpinLock,
+};
+
+use crate::{
+    command::{NvmeCommand, NvmeCompletion, COMMAND_SIZE, COMPLETION_SIZE},
+    NvmeError,
+};
+
+/// The offset of the first doorbell register.
+const DOORBELL_BASE: usize = 0x1000;
+
+/// A queue pair, each of whose commands carries a context of type `C` until it is completed.
+///
+/// The commands are queued in software if the SQ is full, and they are submitted when the
+/// outstanding commands are completed.
+pub(crate) struct QueuePair<C> {
+    qid: u16,
+    size: u16,
+    sq: DmaCoherent,
+    cq: DmaCoherent,
+    io_mem: IoMem,
+    sq_doorbell: usize,
+    cq_doorbell: usize,
+    state: SpinLock<QueueState<C>>,
+}
+
+struct QueueState<C> {
+    sq_tail: u16,
+    cq_head: u16,
+    /// The phase tag of the new completions.
+    phase: bool,
+    /// The allocator of the command IDs.
+    ///
+    /// At most `size - 1` commands are outstanding, so the SQ is never full when a command ID
+    /// can be allocated.
+    cid_allocator: IdAlloc,
+    /// The contexts of the outstanding commands, which are indexed by their command IDs.
+    contexts: Vec<Option<C>>,
+    /// The commands that wait for free slots.
+    pending: VecDeque<(NvmeCommand, C)>,
+}
+
+impl<C> QueueState<C> {
+    fn new(size: u16) -> Self {
+        let mut contexts = Vec::with_capacity(size as usize - 1);
+        contexts.resize_with(size as usize - 1, || None);
+
+        Self {
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            cid_allocator: IdAlloc::with_capacity(size as usize - 1),
+            contexts,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether the completion whose status field is `status` is posted after the
+    /// completions that have been consumed.
+    fn is_new_completion(&self, status: u16) -> bool {
+        (status & 1 != 0) == self.phase
+    }
+
+    fn advance_sq_tail(&mut self, size: u16) {
+        self.sq_tail = (self.sq_tail + 1) % size;
+    }
+
+    /// Advances the CQ head, and inverts the expected phase tag if the CQ wraps around.
+    fn advance_cq_head(&mut self, size: u16) {
+        self.cq_head += 1;
+        if self.cq_head == size {
+            self.cq_head = 0;
+            self.phase = !self.phase;
+        }
+    }
+}
+
+impl<C> QueuePair<C> {
+    /// Creates a queue pair whose queues have `size` entries.
+    ///
+    /// The queues must be registered to the controller before any commands are submitted.
+    pub(crate) fn new(
+        qid: u16,
+        size: u16,
+        io_mem: IoMem,
+        doorbell_stride: usize,
+        location: PciDeviceLocation,
+    ) -> Result<Self, NvmeError> {
+        let sq = alloc_ring(size as usize * COMMAND_SIZE, location)?;
+        let cq = alloc_ring(size as usize * COMPLETION_SIZE, location)?;
+
+        let (sq_doorbell, cq_doorbell) = doorbell_offsets(qid, doorbell_stride);
+
+        Ok(Self {
+            qid,
+            size,
+            sq,
+            cq,
+            io_mem,
+            sq_doorbell,
+            cq_doorbell,
+            state: SpinLock::new(QueueState::new(size)),
+        })
+    }
+
+    pub(crate) fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Returns the device address of the SQ.
+    pub(crate) fn sq_daddr(&self) -> u64 {
+        self.sq.daddr() as u64
+    }
+
+    /// Returns the device address of the CQ.
+    pub(crate) fn cq_daddr(&self) -> u64 {
+        self.cq.daddr() as u64
+    }
+
+    /// Submits the command, or queues it if the SQ is full.
+    ///
+    /// The command ID is filled in by this method.
+    pub(crate) fn submit(&self, command: NvmeCommand, context: C) {
+        let mut state = self.state.disable_irq().lock();
+        // Keep the order of the commands.
+        if !state.pending.is_empty() {
+            state.pending.push_back((command, context));
+            return;
+        }
+        if let Err(pending) = self.try_submit(&mut state, command, context) {
+            state.pending.push_back(pending);
+        }
+    }
+
+    /// Pops the new completions, and calls `f` with each completion and the context of its
+    /// command.
+    ///
+    /// The pending commands are submitted after the completions free some slots. Returns the
+    /// number of the popped completions.
+    pub(crate) fn poll(&self, mut f: impl FnMut(NvmeCompletion, C)) -> usize {
+        let mut completed = Vec::new();
+
+        {
+            let mut state = self.state.disable_irq().lock();
+            loop {
+                let offset = state.cq_head as usize * COMPLETION_SIZE;
+                let status: u16 = self
+                    .cq
+                    .read_once(offset + NvmeCompletion::STATUS_OFFSET)
+                    .unwrap();
+                if !state.is_new_completion(status) {
+                    break;
+                }
+                // Read the other fields after the phase tag.
+                fence(Ordering::SeqCst);
+                let completion: NvmeCompletion = self.cq.read_val(offset).unwrap();
+
+                state.advance_cq_head(self.size);
+
+                let cid = completion.cid as usize;
+                let Some(context) = state.contexts.get_mut(cid).and_then(Option::take) else {
+                    warn!(
+                        "[NVMe]: Queue {} got a completion of an unknown command {}",
+                        self.qid, cid
+                    );
+                    continue;
+                };
+                state.cid_allocator.free(cid);
+                completed.push((completion, context));
+            }
+
+            if completed.is_empty() {
+                return 0;
+            }
+            self.io_mem
+                .write_once(self.cq_doorbell, &(state.cq_head as u32))
+                .unwrap();
+
+            while let Some((command, context)) = state.pending.pop_front() {
+                if let Err((command, context)) = self.try_submit(&mut state, command, context) {
+                    state.pending.push_front((command, context));
+                    break;
+                }
+            }
+        }
+
+        let nr_completed = completed.len();
+        for (completion, context) in completed {
+            f(completion, context);
+        }
+        nr_completed
+    }
+
+    /// Writes the command to the SQ and rings the doorbell if a command ID is free.
+    fn try_submit(
+        &self,
+        state: &mut QueueState<C>,
+        mut command: NvmeCommand,
+        context: C,
+    ) -> Result<(), (NvmeCommand, C)> {
+        let Some(cid) = state.cid_allocator.alloc() else {
+            return Err((command, context));
+        };
+        command.cid = cid as u16;
+        state.contexts[cid] = Some(context);
+
+        self.sq
+            .write_val(state.sq_tail as usize * COMMAND_SIZE, &command)
+            .unwrap();
+        state.advance_sq_tail(self.size);
+        // Make the command visible to the controller before ringing the doorbell.
+        fence(Ordering::SeqCst);
+        self.io_mem
+            .write_once(self.sq_doorbell, &(state.sq_tail as u32))
+            .unwrap();
+
+        Ok(())
+    }
+}
+
+/// Returns the offsets of the SQ tail doorbell and the CQ head doorbell of the queue pair.
+fn doorbell_offsets(qid: u16, doorbell_stride: usize) -> (usize, usize) {
+    let sq_doorbell = DOORBELL_BASE + (2 * qid as usize) * doorbell_stride;
+    (sq_doorbell, sq_doorbell + doorbell_stride)
+}
+
+fn alloc_ring(nbytes: usize, location: PciDeviceLocation) -> Result<DmaCoherent, NvmeError> {
+    let segment = FrameAllocOptions::new()
+        .alloc_segment(nbytes.div_ceil(PAGE_SIZE))
+        .map_err(|_| NvmeError::NoMemory)?;
+    DmaCoherent::map_for_device(segment.into(), true, location).map_err(|_| NvmeError::NoMemory)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn sq_tail_wraps() {
+        let mut state = QueueState::<()>::new(4);
+        for expected in [1, 2, 3, 0, 1] {
+            state.advance_sq_tail(4);
+            assert_eq!(state.sq_tail, expected);
+        }
+    }
+
+    #[ktest]
+    fn cq_phase_inverts() {
+        const PHASE: u16 = 1;
+        const STATUS: u16 = 0x2 << 1;
+
+        let mut state = QueueState::<()>::new(4);
+        // The CQ is zeroed initially, so no completions are new until the controller posts
+        // ones with the phase tag set.
+        assert!(!state.is_new_completion(0));
+        assert!(state.is_new_completion(PHASE));
+        assert!(state.is_new_completion(STATUS | PHASE));
+
+        for expected in [1, 2, 3] {
+            state.advance_cq_head(4);
+            assert_eq!((state.cq_head, state.phase), (expected, true));
+        }
+        state.advance_cq_head(4);
+        assert_eq!((state.cq_head, state.phase), (0, false));
+
+        // The completions left from the last pass are no longer new.
+        assert!(!state.is_new_completion(STATUS | PHASE));
+        assert!(state.is_new_completion(STATUS));
+
+        for _ in 0..4 {
+            state.advance_cq_head(4);
+        }
+        assert_eq!((state.cq_head, state.phase), (0, true));
+    }
+
+    #[ktest]
+    fn outstanding_commands() {
+        let mut state = QueueState::<()>::new(4);
+        // One slot is left empty, so the SQ never looks empty when it is full.
+        let cids: Vec<_> = core::iter::from_fn(|| state.cid_allocator.alloc()).collect();
+        assert_eq!(cids, [0, 1, 2]);
+        assert_eq!(state.contexts.len(), 3);
+
+        state.cid_allocator.free(1);
+        assert_eq!(state.cid_allocator.alloc(), Some(1));
+        assert_eq!(state.cid_allocator.alloc(), None);
+    }
+
+    #[ktest]
+    fn doorbells() {
+        assert_eq!(doorbell_offsets(0, 4), (0x1000, 0x1004));
+        assert_eq!(doorbell_offsets(1, 4), (0x1008, 0x100c));
+        assert_eq!(doorbell_offsets(3, 16), (0x1060, 0x1070));
+    }
+}
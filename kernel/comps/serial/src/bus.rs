@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of serial ports.
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use log::info;
+use ostd::sync::{LocalIrqDisabled, Mutex, SpinLock};
+
+use crate::{SerialError, SerialPort};
+
+/// The number of the lines reserved for the legacy ISA ports.
+pub const NR_ISA_LINES: u32 = 4;
+
+/// Registers a port and returns its line number.
+///
+/// If `line` is `None`, the first free line after the ones reserved for the ISA ports is
+/// assigned. Otherwise, this method fails if the line is in use.
+pub fn register_port(port: Arc<SerialPort>, line: Option<u32>) -> Result<u32, SerialError> {
+    let mut ports = SERIAL_PORTS.lock();
+
+    let line = match line {
+        Some(line) if ports.contains_key(&line) => return Err(SerialError::Busy),
+        Some(line) => line,
+        None => (NR_ISA_LINES..)
+            .find(|line| !ports.contains_key(line))
+            .unwrap(),
+    };
+    info!(
+        "[Serial]: Registered line {}: {} at {}",
+        line,
+        port.uart_type().name(),
+        port.name()
+    );
+    ports.insert(line, port);
+
+    Ok(line)
+}
+
+/// Returns the port of the line.
+pub fn get_port(line: u32) -> Option<Arc<SerialPort>> {
+    SERIAL_PORTS.lock().get(&line).cloned()
+}
+
+/// Returns all the ports with their line numbers.
+pub fn all_ports() -> Vec<(u32, Arc<SerialPort>)> {
+    SERIAL_PORTS
+        .lock()
+        .iter()
+        .map(|(line, port)| (*line, port.clone()))
+        .collect()
+}
+
+static SERIAL_PORTS: Mutex<BTreeMap<u32, Arc<SerialPort>>> = Mutex::new(BTreeMap::new());
+
+/// The ports whose interrupts are not available, which are polled on timer interrupts.
+static POLLED_PORTS: SpinLock<Vec<Arc<SerialPort>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// Polls the port on timer interrupts.
+pub(crate) fn add_polled_port(port: Arc<SerialPort>) {
+    POLLED_PORTS.lock().push(port);
+}
+
+/// Polls all the ports that do not have interrupts.
+pub(crate) fn poll_all() {
+    for port in POLLED_PORTS.lock().iter() {
+        port.handle_irq();
+    }
+}
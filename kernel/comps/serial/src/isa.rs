@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The serial ports on the ISA bus and the one declared by the firmware.
+//!
+//! The legacy COM ports are probed at their well-known I/O ports. The port used by the early
+//! console is handed over from the console, and the console output is sent by the port since
+//! then, so the port can be driven with interrupts like the others.
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc};
+
+use log::{info, warn};
+use ostd::{
+    arch::{
+        device::{
+            isa,
+            serial::{firmware_console_port, SerialPortBase},
+        },
+        serial::take_console_port,
+    },
+    io::IoMem,
+    sync::Mutex,
+    trap::IrqLine,
+};
+use spin::Once;
+
+use crate::{
+    bus::{self, add_polled_port},
+    port::PC_UART_CLOCK,
+    uart::UartIo,
+    SerialPort,
+};
+
+/// The I/O ports and the IRQs of COM1 to COM4, whose line numbers are their indices.
+const LEGACY_PORTS: [(u16, u8); 4] = [(0x3f8, 4), (0x2f8, 3), (0x3e8, 4), (0x2e8, 3)];
+
+pub(super) fn init() {
+    let firmware_port = firmware_console_port();
+    let mut console_port = take_console_port(console_output);
+
+    for (line, (base, irq)) in LEGACY_PORTS.into_iter().enumerate() {
+        // Use the baud rate configured by the firmware to keep the remote side working.
+        let mut baud_rate = firmware_port
+            .filter(|port| port.base == SerialPortBase::Pio(base))
+            .and_then(|port| port.baud_rate);
+
+        let is_console = console_port
+            .as_ref()
+            .is_some_and(|console| console.base == base);
+        let io = if is_console {
+            // Keep the baud rate used by the console, which has been printing the logs.
+            let console = console_port.take().unwrap();
+            baud_rate = Some(console.baud_rate);
+            UartIo::Pio(console.io_ports)
+        } else {
+            match UartIo::acquire_pio(base) {
+                Ok(io) => io,
+                Err(_) => {
+                    info!("[Serial]: The I/O ports at {:#x} are in use", base);
+                    continue;
+                }
+            }
+        };
+        let name = format!("I/O port {:#x}", base);
+        let Some(port) = SerialPort::new(name, io, PC_UART_CLOCK, baud_rate) else {
+            continue;
+        };
+        if is_console {
+            CONSOLE_PORT.call_once(|| port.clone());
+        }
+        add_port(port, Some(line as u32), Some(irq));
+    }
+
+    // The firmware may use a port that is not a legacy COM port, e.g., a memory-mapped UART.
+    if let Some(firmware_port) = firmware_port {
+        if let Some(port) = probe_firmware_port(&firmware_port.base, firmware_port.reg_shift)
+            .and_then(|(name, io)| {
+                SerialPort::new(name, io, PC_UART_CLOCK, firmware_port.baud_rate)
+            })
+        {
+            add_port(port, None, firmware_port.isa_irq);
+        }
+    }
+}
+
+fn probe_firmware_port(base: &SerialPortBase, reg_shift: u8) -> Option<(String, UartIo)> {
+    match *base {
+        SerialPortBase::Pio(base) => {
+            if LEGACY_PORTS
+                .iter()
+                .any(|(legacy_base, _)| *legacy_base == base)
+            {
+                return None;
+            }
+            let io = UartIo::acquire_pio(base).ok()?;
+            Some((format!("I/O port {:#x}", base), io))
+        }
+        SerialPortBase::Mmio(paddr) => {
+            let io_mem = IoMem::acquire(paddr..paddr + UartIo::region_len(reg_shift)).ok()?;
+            let io = UartIo::Mmio { io_mem, reg_shift };
+            Some((format!("MMIO {:#x}", paddr), io))
+        }
+    }
+}
+
+fn add_port(port: Arc<SerialPort>, line: Option<u32>, irq: Option<u8>) {
+    if let Err(err) = bus::register_port(port.clone(), line) {
+        warn!("[Serial]: Failed to register {}: {:?}", port.name(), err);
+        return;
+    }
+
+    let is_irq_attached = irq.is_some_and(|irq| attach_irq(irq, port.clone()));
+    if !is_irq_attached {
+        add_polled_port(port);
+    }
+}
+
+/// The port that has been handed over from the console.
+static CONSOLE_PORT: Once<Arc<SerialPort>> = Once::new();
+
+/// Sends a byte of the console output.
+///
+/// The console sends the byte by itself if this returns `false`, e.g., before the port is ready.
+fn console_output(ch: u8) -> bool {
+    CONSOLE_PORT
+        .get()
+        .is_some_and(|port| port.write_console(ch))
+}
+
+/// The IRQ lines of the ISA IRQs, which may be shared by multiple ports.
+static ISA_IRQ_LINES: Mutex<BTreeMap<u8, IrqLine>> = Mutex::new(BTreeMap::new());
+
+fn attach_irq(isa_irq: u8, port: Arc<SerialPort>) -> bool {
+    let mut irq_lines = ISA_IRQ_LINES.lock();
+
+    if !irq_lines.contains_key(&isa_irq) {
+        let Ok(irq_line) = IrqLine::alloc() else {
+            return false;
+        };
+        if let Err(err) = isa::route_irq(isa_irq, irq_line.clone()) {
+            warn!("[Serial]: Failed to route ISA IRQ {}: {:?}", isa_irq, err);
+            return false;
+        }
+        irq_lines.insert(isa_irq, irq_line);
+    }
+
+    let irq_line = irq_lines.get_mut(&isa_irq).unwrap();
+    irq_line.on_active(move |_| port.handle_irq());
+    true
+}
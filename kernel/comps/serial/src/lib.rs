@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The serial subsystem of Asterinas.
+//!
+//! The subsystem drives the 8250/16550-compatible UARTs, which are found as:
+//!  - the legacy COM ports on the ISA bus and the port declared by the ACPI SPCR table (x86 only);
+//!  - the PCI serial cards whose programming interface is 16550.
+//!
+//! Each port ([`SerialPort`]) is identified by its line number, which is assigned when the port is
+//! registered. The lines of the legacy COM ports are fixed to be 0 to 3, so they match the names
+//! used by Linux. Userspace accesses the ports via `/dev/ttySN`, which is implemented by the kernel
+//! on top of [`get_port`].
+//!
+//! The port used by the early console is taken over by this subsystem, which then sends the
+//! console output with the port locked. So the console output and the interrupt-driven
+//! transmission of the port do not interfere with each other.
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod bus;
+#[cfg(target_arch = "x86_64")]
+mod isa;
+mod pci;
+mod port;
+mod uart;
+
+pub use bus::{all_ports, get_port, register_port, NR_ISA_LINES};
+use component::{init_component, ComponentInitError};
+pub use port::{Parity, SerialConfig, SerialListener, SerialPort, StopBits, PC_UART_CLOCK};
+pub use uart::{UartIo, UartType};
+
+/// The errors of serial ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// The line is used by another port.
+    Busy,
+}
+
+#[init_component]
+fn serial_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "x86_64")]
+    isa::init();
+    pci::init();
+    ostd::timer::register_callback(bus::poll_all);
+    Ok(())
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI serial cards that are compatible with 16550.
+//!
+//! The legacy INTx interrupts are not supported yet, so the ports on the cards are polled.
+
+use alloc::{format, sync::Arc, vec::Vec};
+
+use log::warn;
+use ostd::bus::{
+    pci::{
+        bus::{PciDevice, PciDriver},
+        cfg_space::{Bar, Command},
+        common_device::PciCommonDevice,
+        PciDeviceId, PCI_BUS,
+    },
+    BusProbeError,
+};
+
+use crate::{
+    bus::{self, add_polled_port},
+    port::PC_UART_CLOCK,
+    uart::{UartIo, NR_REGS},
+    SerialPort,
+};
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(SerialPciDriver));
+}
+
+const SIMPLE_COMM_CLASS: u8 = 0x07;
+const SERIAL_SUBCLASS: u8 = 0x00;
+const PROG_IF_16550: u8 = 0x02;
+
+const REDHAT_VENDOR_ID: u16 = 0x1b36;
+/// The multi-port cards of QEMU, whose ports are consecutive in BAR 0.
+const QEMU_MULTI_PORT_CARDS: [(u16, usize); 2] = [(0x0003, 2), (0x0004, 4)];
+
+#[derive(Debug)]
+struct SerialPciDriver;
+
+impl PciDriver for SerialPciDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.class != SIMPLE_COMM_CLASS
+            || device_id.subclass != SERIAL_SUBCLASS
+            || device_id.prog_if != PROG_IF_16550
+        {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        let nr_ports = if device_id.vendor_id == REDHAT_VENDOR_ID {
+            QEMU_MULTI_PORT_CARDS
+                .iter()
+                .find(|(id, _)| *id == device_id.device_id)
+                .map_or(1, |(_, nr_ports)| *nr_ports)
+        } else {
+            1
+        };
+
+        let Some(bar) = device.bar_manager().bar(0).clone() else {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        };
+        let (bar_size, command) = match &bar {
+            Bar::Io(io_bar) => (io_bar.size() as usize, Command::IO_SPACE),
+            Bar::Memory(memory_bar) => (memory_bar.size() as usize, Command::MEMORY_SPACE),
+        };
+        if bar_size < nr_ports * NR_REGS as usize {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        }
+        device.set_command(device.command() | command);
+
+        let location = *device.location();
+        let mut ports = Vec::with_capacity(nr_ports);
+        for index in 0..nr_ports {
+            let io = UartIo::Bar {
+                bar: bar.clone(),
+                offset: index * NR_REGS as usize,
+            };
+            let name = format!(
+                "PCI {:02x}:{:02x}.{} port {}",
+                location.bus, location.device, location.function, index
+            );
+            let Some(port) = SerialPort::new(name, io, PC_UART_CLOCK, None) else {
+                continue;
+            };
+            if let Err(err) = bus::register_port(port.clone(), None) {
+                warn!("[Serial]: Failed to register {}: {:?}", port.name(), err);
+                continue;
+            }
+            add_polled_port(port.clone());
+            ports.push(port);
+        }
+
+        if ports.is_empty() {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+        Ok(Arc::new(SerialPciDevice { device_id, ports }))
+    }
+}
+
+#[derive(Debug)]
+struct SerialPciDevice {
+    device_id: PciDeviceId,
+    #[expect(dead_code)]
+    ports: Vec<Arc<SerialPort>>,
+}
+
+impl PciDevice for SerialPciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
@@ -0,0 +1,704 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Serial ports driven by 8250/16550-compatible UARTs.
+
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::fmt::Debug;
+
+use ostd::sync::{LocalIrqDisabled, RingBuffer, SpinLock};
+
+use crate::{
+    uart::{
+        Fcr, Ier, Lcr, Lsr, Mcr, Msr, UartIo, UartType, DLL, DLM, IER, IIR_FCR, LCR, LSR, MCR, MSR,
+        RBR_THR,
+    },
+    SerialError,
+};
+
+/// The clock of the UARTs on PC-compatible boards in Hz.
+pub const PC_UART_CLOCK: u32 = 1_843_200;
+
+/// The size of the transmit buffer.
+const TX_BUF_SIZE: usize = 4096;
+/// The writers are notified when the room of the transmit buffer exceeds this threshold.
+const TX_WAKEUP_THRESHOLD: usize = 256;
+/// The maximum number of bytes delivered to the listener at once.
+const RX_BATCH_SIZE: usize = 64;
+/// The number of the attempts to lock the port before the console output gives up.
+const CONSOLE_LOCK_ATTEMPTS: usize = 1 << 20;
+
+/// The parity of the serial line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    /// The parity bit is always one.
+    Mark,
+    /// The parity bit is always zero.
+    Space,
+}
+
+/// The number of the stop bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    /// Two stop bits, or 1.5 stop bits if the words are 5-bit.
+    Two,
+}
+
+/// The configuration of a serial port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// The baud rate, where zero hangs up the line by dropping DTR and RTS.
+    pub baud_rate: u32,
+    /// The number of the data bits, which is from 5 to 8.
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// Whether the bytes are received.
+    pub receiver_enabled: bool,
+    /// Whether the hardware flow control with RTS/CTS is enabled.
+    pub rts_cts: bool,
+    /// Whether the transmission is stopped and started by the STOP and START characters received.
+    pub ixon: bool,
+    /// Whether the STOP and START characters are sent when the port is throttled and unthrottled.
+    pub ixoff: bool,
+    /// The START character, which is XON by default.
+    pub start_char: u8,
+    /// The STOP character, which is XOFF by default.
+    pub stop_char: u8,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            receiver_enabled: true,
+            rts_cts: false,
+            ixon: false,
+            ixoff: false,
+            start_char: 0x11,
+            stop_char: 0x13,
+        }
+    }
+}
+
+/// The listener of the events of a serial port.
+///
+/// The methods are called in the interrupt context, so they must not sleep.
+pub trait SerialListener: Send + Sync {
+    /// Called when some bytes are received.
+    fn on_receive(&self, bytes: &[u8]);
+
+    /// Called when the transmit buffer has more room or becomes empty.
+    fn on_transmit_ready(&self);
+}
+
+/// A serial port.
+///
+/// The bytes to transmit are buffered and sent from the interrupt handler. The received bytes are
+/// delivered to the [`SerialListener`] from the interrupt handler.
+pub struct SerialPort {
+    name: String,
+    uart_type: UartType,
+    uart_clock: u32,
+    inner: SpinLock<PortInner, LocalIrqDisabled>,
+    /// The bytes to transmit.
+    ///
+    /// The bytes are pushed and popped with `inner` locked, so the bytes of a write are not
+    /// interleaved with those of other writes. But the state of the buffer can be queried
+    /// without the lock.
+    tx_buf: RingBuffer<u8>,
+    listener: SpinLock<Option<Weak<dyn SerialListener>>, LocalIrqDisabled>,
+}
+
+struct PortInner {
+    io: UartIo,
+    config: SerialConfig,
+    ier: Ier,
+    mcr: Mcr,
+    /// Whether the transmission is stopped by the STOP character.
+    is_tx_stopped: bool,
+    /// Whether the remote side is asked to stop the transmission.
+    is_throttled: bool,
+    /// Whether CTS is asserted.
+    cts: bool,
+    /// The flow control character to send before the buffered bytes.
+    x_char: Option<u8>,
+}
+
+impl SerialPort {
+    /// Creates a serial port with the UART behind `io`.
+    ///
+    /// If `baud_rate` is `None`, the default configuration is used. Returns `None` if no UART is
+    /// found.
+    pub fn new(
+        name: String,
+        io: UartIo,
+        uart_clock: u32,
+        baud_rate: Option<u32>,
+    ) -> Option<Arc<Self>> {
+        let uart_type = UartType::detect(&io)?;
+
+        let mut config = SerialConfig::default();
+        if let Some(baud_rate) = baud_rate {
+            config.baud_rate = baud_rate;
+        }
+        let port = Self {
+            name,
+            uart_type,
+            uart_clock,
+            inner: SpinLock::new(PortInner {
+                io,
+                config,
+                ier: Ier::empty(),
+                mcr: Mcr::OUT2,
+                is_tx_stopped: false,
+                is_throttled: false,
+                cts: false,
+                x_char: None,
+            }),
+            tx_buf: RingBuffer::new(TX_BUF_SIZE),
+            listener: SpinLock::new(None),
+        };
+        port.startup(config).ok()?;
+
+        Some(Arc::new(port))
+    }
+
+    fn startup(&self, config: SerialConfig) -> Result<(), SerialError> {
+        let mut inner = self.inner.lock();
+
+        inner.io.write(IER, 0);
+        if self.uart_type.fifo_size() > 1 {
+            let fcr = Fcr::ENABLE | Fcr::CLEAR_RCVR | Fcr::CLEAR_XMIT;
+            inner.io.write(IIR_FCR, fcr.bits());
+        }
+        // Clear the pending interrupts.
+        inner.io.read(LSR);
+        inner.io.read(RBR_THR);
+        inner.io.read(IIR_FCR);
+        inner.io.read(MSR);
+
+        self.apply_config(&mut inner, config)?;
+
+        // The transmitter holding register empty interrupt is enabled on demand.
+        inner.ier = Ier::RDI | Ier::RLSI | Ier::MSI;
+        inner.io.write(IER, inner.ier.bits());
+
+        Ok(())
+    }
+
+    /// Returns the name of the port, which describes where the port is.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the UART.
+    pub fn uart_type(&self) -> UartType {
+        self.uart_type
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> SerialConfig {
+        self.inner.lock().config
+    }
+
+    /// Sets the configuration.
+    ///
+    /// This method fails if the configuration is not supported, in which case the configuration
+    /// is not changed.
+    pub fn set_config(&self, config: &SerialConfig) -> Result<(), SerialError> {
+        let mut inner = self.inner.lock();
+        self.apply_config(&mut inner, *config)?;
+        let should_wake = self.transmit_chars(&mut inner);
+        drop(inner);
+
+        if should_wake {
+            self.notify_transmit_ready();
+        }
+        Ok(())
+    }
+
+    fn apply_config(&self, inner: &mut PortInner, config: SerialConfig) -> Result<(), SerialError> {
+        let mut lcr = match config.data_bits {
+            5 => Lcr::WLEN5,
+            6 => Lcr::WLEN6,
+            7 => Lcr::WLEN7,
+            8 => Lcr::WLEN8,
+            _ => return Err(SerialError::InvalidArgs),
+        };
+        if config.stop_bits == StopBits::Two {
+            lcr |= Lcr::STOP;
+        }
+        lcr |= match config.parity {
+            Parity::None => Lcr::empty(),
+            Parity::Odd => Lcr::PARITY,
+            Parity::Even => Lcr::PARITY | Lcr::EPAR,
+            Parity::Mark => Lcr::PARITY | Lcr::SPAR,
+            Parity::Space => Lcr::PARITY | Lcr::EPAR | Lcr::SPAR,
+        };
+
+        let divisor = if config.baud_rate == 0 {
+            None
+        } else {
+            // Round the divisor to the nearest integer.
+            let baud_rate = config.baud_rate as u64;
+            let divisor = (self.uart_clock as u64 + baud_rate * 8) / (baud_rate * 16);
+            if divisor == 0 || divisor > u16::MAX as u64 {
+                return Err(SerialError::InvalidArgs);
+            }
+            Some(divisor as u16)
+        };
+
+        let io = &inner.io;
+        // The FIFO control register is written with the divisor latch accessible, which is
+        // required to enable the 64-byte FIFOs of 16750.
+        io.write(LCR, (lcr | Lcr::DLAB).bits());
+        if let Some(divisor) = divisor {
+            let [low, high] = divisor.to_le_bytes();
+            io.write(DLL, low);
+            io.write(DLM, high);
+        }
+        io.write(IIR_FCR, self.uart_type.fcr().bits());
+        io.write(LCR, lcr.bits());
+
+        if divisor.is_none() {
+            inner.mcr.remove(Mcr::DTR | Mcr::RTS);
+        } else if inner.is_throttled && config.rts_cts {
+            inner.mcr.insert(Mcr::DTR);
+            inner.mcr.remove(Mcr::RTS);
+        } else {
+            inner.mcr.insert(Mcr::DTR | Mcr::RTS);
+        }
+        inner.io.write(MCR, inner.mcr.bits());
+
+        if !config.ixon {
+            inner.is_tx_stopped = false;
+        }
+        inner.cts = Msr::from_bits_truncate(inner.io.read(MSR)).contains(Msr::CTS);
+        inner.config = config;
+
+        Ok(())
+    }
+
+    /// Sets the listener of the events.
+    pub fn set_listener(&self, listener: Weak<dyn SerialListener>) {
+        *self.listener.lock() = Some(listener);
+    }
+
+    fn listener(&self) -> Option<Arc<dyn SerialListener>> {
+        self.listener.lock().as_ref().and_then(Weak::upgrade)
+    }
+
+    fn notify_transmit_ready(&self) {
+        if let Some(listener) = self.listener() {
+            listener.on_transmit_ready();
+        }
+    }
+
+    /// Writes the bytes to the transmit buffer without blocking.
+    ///
+    /// Returns the number of the bytes written, which is zero if the buffer is full. This method
+    /// can be called in the interrupt context.
+    pub fn write(&self, bytes: &[u8]) -> usize {
+        let mut inner = self.inner.lock();
+
+        let len = bytes
+            .iter()
+            .take_while(|byte| self.tx_buf.push(**byte).is_ok())
+            .count();
+        self.transmit_chars(&mut inner);
+
+        len
+    }
+
+    /// Returns whether the transmit buffer has room for more bytes.
+    pub fn can_write(&self) -> bool {
+        !self.tx_buf.is_full()
+    }
+
+    /// Returns whether all the buffered bytes have been moved to the UART.
+    pub fn is_tx_empty(&self) -> bool {
+        let inner = self.inner.lock();
+        self.tx_buf.is_empty() && inner.x_char.is_none()
+    }
+
+    /// Discards the bytes in the transmit buffer.
+    pub fn flush_tx(&self) {
+        let inner = self.inner.lock();
+        self.tx_buf.clear();
+        drop(inner);
+        self.notify_transmit_ready();
+    }
+
+    /// Asks the remote side to stop the transmission.
+    ///
+    /// Depending on the configuration, the STOP character is sent and/or RTS is dropped.
+    pub fn throttle(&self) {
+        self.set_throttled(true);
+    }
+
+    /// Asks the remote side to resume the transmission.
+    pub fn unthrottle(&self) {
+        self.set_throttled(false);
+    }
+
+    fn set_throttled(&self, is_throttled: bool) {
+        let mut inner = self.inner.lock();
+        if inner.is_throttled == is_throttled {
+            return;
+        }
+        inner.is_throttled = is_throttled;
+
+        let config = inner.config;
+        if config.ixoff {
+            inner.x_char = Some(if is_throttled {
+                config.stop_char
+            } else {
+                config.start_char
+            });
+        }
+        if config.rts_cts && config.baud_rate != 0 {
+            inner.mcr.set(Mcr::RTS, !is_throttled);
+            inner.io.write(MCR, inner.mcr.bits());
+        }
+
+        self.transmit_chars(&mut inner);
+    }
+
+    /// Sends a byte of the console output by polling the UART.
+    ///
+    /// The byte is sent before the bytes in the transmit buffer that have not been moved to the
+    /// UART. Returns `false` if the port cannot be locked, e.g., because the code holding the
+    /// lock is interrupted by a kernel panic.
+    pub(crate) fn write_console(&self, ch: u8) -> bool {
+        let Some(inner) = (0..CONSOLE_LOCK_ATTEMPTS).find_map(|_| self.inner.try_lock()) else {
+            return false;
+        };
+
+        while !Lsr::from_bits_truncate(inner.io.read(LSR)).contains(Lsr::THRE) {
+            core::hint::spin_loop();
+        }
+        inner.io.write(RBR_THR, ch);
+
+        true
+    }
+
+    /// Handles the interrupt of the UART.
+    ///
+    /// The ports without interrupts are polled with this method.
+    pub(crate) fn handle_irq(&self) {
+        let mut buf = [0u8; RX_BATCH_SIZE];
+
+        loop {
+            let mut inner = self.inner.lock();
+            let (len, nr_read) = self.receive_chars(&mut inner, &mut buf);
+            inner.cts = Msr::from_bits_truncate(inner.io.read(MSR)).contains(Msr::CTS);
+            let should_wake = self.transmit_chars(&mut inner);
+            drop(inner);
+
+            if let Some(listener) = self.listener() {
+                if len > 0 {
+                    listener.on_receive(&buf[..len]);
+                }
+                if should_wake {
+                    listener.on_transmit_ready();
+                }
+            }
+
+            if nr_read < buf.len() {
+                break;
+            }
+        }
+    }
+
+    /// Receives the bytes into `buf`.
+    ///
+    /// Returns the number of the bytes stored and the number of the bytes read from the UART.
+    /// The bytes with errors and the flow control characters are not stored.
+    fn receive_chars(&self, inner: &mut PortInner, buf: &mut [u8]) -> (usize, usize) {
+        let mut len = 0;
+        let mut nr_read = 0;
+
+        while nr_read < buf.len() {
+            let lsr = Lsr::from_bits_truncate(inner.io.read(LSR));
+            if !lsr.contains(Lsr::DR) {
+                break;
+            }
+            let ch = inner.io.read(RBR_THR);
+            nr_read += 1;
+
+            if lsr.intersects(Lsr::RX_ERRORS) || !inner.config.receiver_enabled {
+                continue;
+            }
+            if inner.config.ixon {
+                if ch == inner.config.stop_char {
+                    inner.is_tx_stopped = true;
+                    continue;
+                }
+                if ch == inner.config.start_char {
+                    inner.is_tx_stopped = false;
+                    continue;
+                }
+            }
+
+            buf[len] = ch;
+            len += 1;
+        }
+
+        (len, nr_read)
+    }
+
+    /// Moves the bytes in the transmit buffer to the UART.
+    ///
+    /// Returns whether the writers should be notified.
+    fn transmit_chars(&self, inner: &mut PortInner) -> bool {
+        let can_transmit = !inner.is_tx_stopped && (!inner.config.rts_cts || inner.cts);
+        let mut has_sent = false;
+
+        if Lsr::from_bits_truncate(inner.io.read(LSR)).contains(Lsr::THRE) {
+            let mut budget = self.uart_type.fifo_size();
+            // The flow control characters are sent even if the transmission is stopped.
+            if let Some(ch) = inner.x_char.take() {
+                inner.io.write(RBR_THR, ch);
+                budget -= 1;
+            }
+            while can_transmit && budget > 0 {
+                let Some(ch) = self.tx_buf.pop() else {
+                    break;
+                };
+                inner.io.write(RBR_THR, ch);
+                budget -= 1;
+                has_sent = true;
+            }
+        }
+
+        // Wait for the next interrupt if there are more bytes to send.
+        let has_pending = inner.x_char.is_some() || (can_transmit && !self.tx_buf.is_empty());
+        if inner.ier.contains(Ier::THRI) != has_pending {
+            inner.ier.set(Ier::THRI, has_pending);
+            inner.io.write(IER, inner.ier.bits());
+        }
+
+        has_sent && TX_BUF_SIZE - self.tx_buf.len() >= TX_WAKEUP_THRESHOLD
+    }
+}
+
+impl Debug for SerialPort {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SerialPort")
+            .field("name", &self.name)
+            .field("uart_type", &self.uart_type)
+            .finish()
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::{string::ToString, vec::Vec};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{bus, uart::mock::MockUart};
+
+    const XON: u8 = 0x11;
+    const XOFF: u8 = 0x13;
+
+    struct MockListener {
+        received: SpinLock<Vec<u8>, LocalIrqDisabled>,
+        nr_transmit_ready: AtomicUsize,
+    }
+
+    impl MockListener {
+        fn new() -> Self {
+            Self {
+                received: SpinLock::new(Vec::new()),
+                nr_transmit_ready: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl SerialListener for MockListener {
+        fn on_receive(&self, bytes: &[u8]) {
+            self.received.lock().extend_from_slice(bytes);
+        }
+
+        fn on_transmit_ready(&self) {
+            self.nr_transmit_ready.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn new_port(baud_rate: u32) -> (Arc<MockUart>, Arc<SerialPort>) {
+        let uart = MockUart::new();
+        let io = UartIo::Mock(uart.clone());
+        let port = SerialPort::new("mock".to_string(), io, PC_UART_CLOCK, Some(baud_rate));
+        (uart, port.unwrap())
+    }
+
+    #[ktest]
+    fn detect_and_configure() {
+        let (uart, port) = new_port(115200);
+        assert_eq!(port.uart_type(), UartType::U16550A);
+        assert_eq!(uart.divisor(), 1);
+        assert_eq!(uart.lcr(), Lcr::WLEN8);
+        assert_eq!(uart.mcr(), Mcr::DTR | Mcr::RTS | Mcr::OUT2);
+        assert_eq!(uart.ier(), Ier::RDI | Ier::RLSI | Ier::MSI);
+
+        let config = SerialConfig {
+            baud_rate: 9600,
+            data_bits: 7,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+            ..Default::default()
+        };
+        port.set_config(&config).unwrap();
+        assert_eq!(port.config(), config);
+        assert_eq!(uart.divisor(), 12);
+        assert_eq!(uart.lcr(), Lcr::WLEN7 | Lcr::STOP | Lcr::PARITY | Lcr::EPAR);
+
+        // Unsupported configurations are rejected without changing anything.
+        let invalid_configs = [
+            SerialConfig {
+                data_bits: 9,
+                ..config
+            },
+            // The divisor does not fit in 16 bits.
+            SerialConfig {
+                baud_rate: 1,
+                ..config
+            },
+        ];
+        for invalid_config in invalid_configs {
+            assert_eq!(
+                port.set_config(&invalid_config),
+                Err(SerialError::InvalidArgs)
+            );
+            assert_eq!(port.config(), config);
+            assert_eq!(uart.divisor(), 12);
+        }
+
+        // A zero baud rate hangs up the line.
+        port.set_config(&SerialConfig {
+            baud_rate: 0,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(uart.mcr(), Mcr::OUT2);
+    }
+
+    #[ktest]
+    fn register() {
+        let (_, port) = new_port(115200);
+        let line = bus::register_port(port.clone(), None).unwrap();
+        assert!(line >= bus::NR_ISA_LINES);
+        assert!(Arc::ptr_eq(&bus::get_port(line).unwrap(), &port));
+
+        let (_, other_port) = new_port(115200);
+        assert_eq!(
+            bus::register_port(other_port, Some(line)),
+            Err(SerialError::Busy)
+        );
+    }
+
+    #[ktest]
+    fn transmit_and_receive() {
+        let (uart, port) = new_port(115200);
+        let listener = Arc::new(MockListener::new());
+        let weak_listener: Weak<dyn SerialListener> = Arc::downgrade(&listener);
+        port.set_listener(weak_listener);
+
+        // The bytes beyond the FIFO are sent on the next interrupt.
+        let bytes = (0..20).collect::<Vec<u8>>();
+        assert_eq!(port.write(&bytes), bytes.len());
+        assert_eq!(uart.take_sent(), bytes[..16]);
+        assert!(uart.ier().contains(Ier::THRI));
+        port.handle_irq();
+        assert_eq!(uart.take_sent(), bytes[16..]);
+        assert!(!uart.ier().contains(Ier::THRI));
+        assert_eq!(listener.nr_transmit_ready.load(Ordering::Relaxed), 1);
+        assert!(port.is_tx_empty());
+
+        // The bytes with errors are dropped.
+        uart.receive(b"ab", Lsr::empty());
+        uart.receive(b"c", Lsr::PE);
+        uart.receive(b"d", Lsr::empty());
+        port.handle_irq();
+        assert_eq!(*listener.received.lock(), b"abd");
+    }
+
+    #[ktest]
+    fn flow_control() {
+        let (uart, port) = new_port(115200);
+        port.set_config(&SerialConfig {
+            ixon: true,
+            ixoff: true,
+            ..port.config()
+        })
+        .unwrap();
+
+        // The transmission is stopped and started by the received characters.
+        uart.receive(&[XOFF], Lsr::empty());
+        port.handle_irq();
+        port.write(b"x");
+        assert!(uart.take_sent().is_empty());
+        uart.receive(&[XON], Lsr::empty());
+        port.handle_irq();
+        assert_eq!(uart.take_sent(), b"x");
+
+        // The characters are sent to throttle and unthrottle the remote side.
+        port.throttle();
+        port.unthrottle();
+        assert_eq!(uart.take_sent(), [XOFF, XON]);
+
+        // The transmission waits for CTS.
+        port.set_config(&SerialConfig {
+            rts_cts: true,
+            ..port.config()
+        })
+        .unwrap();
+        port.write(b"y");
+        assert!(uart.take_sent().is_empty());
+        port.throttle();
+        assert!(!uart.mcr().contains(Mcr::RTS));
+        uart.set_cts(true);
+        port.handle_irq();
+        assert_eq!(uart.take_sent(), [XOFF, b'y']);
+    }
+
+    #[ktest]
+    fn console_output() {
+        let (uart, port) = new_port(115200);
+        port.set_config(&SerialConfig {
+            ixon: true,
+            ..port.config()
+        })
+        .unwrap();
+
+        // The console output is sent before the buffered bytes, even if the transmission is
+        // stopped by the remote side.
+        uart.receive(&[XOFF], Lsr::empty());
+        port.handle_irq();
+        port.write(b"buffered");
+        assert!(port.write_console(b'!'));
+        assert_eq!(uart.take_sent(), b"!");
+        uart.receive(&[XON], Lsr::empty());
+        port.handle_irq();
+        assert_eq!(uart.take_sent(), b"buffered");
+
+        // The console output gives up if the port cannot be locked.
+        let inner = port.inner.lock();
+        assert!(!port.write_console(b'?'));
+        drop(inner);
+        assert!(uart.take_sent().is_empty());
+    }
+}
@@ -0,0 +1,448 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registers of the 8250/16550-compatible UARTs.
+//!
+//! Reference: TI PC16550D Universal Asynchronous Receiver/Transmitter with FIFOs, Section 8.
+
+use core::fmt::Debug;
+
+use bitflags::bitflags;
+#[cfg(target_arch = "x86_64")]
+use ostd::{arch::device::io_port::ReadWriteAccess, io::IoPort};
+use ostd::{bus::pci::cfg_space::Bar, io::IoMem, mm::VmIoOnce};
+
+// The offsets of the registers in units of registers.
+/// The receiver buffer register (read) and the transmitter holding register (write).
+pub(crate) const RBR_THR: u8 = 0;
+/// The interrupt enable register.
+pub(crate) const IER: u8 = 1;
+/// The interrupt identification register (read) and the FIFO control register (write).
+pub(crate) const IIR_FCR: u8 = 2;
+/// The line control register.
+pub(crate) const LCR: u8 = 3;
+/// The modem control register.
+pub(crate) const MCR: u8 = 4;
+/// The line status register.
+pub(crate) const LSR: u8 = 5;
+/// The modem status register.
+pub(crate) const MSR: u8 = 6;
+/// The scratch register.
+pub(crate) const SCR: u8 = 7;
+/// The low byte of the divisor latch, which is accessible if [`Lcr::DLAB`] is set.
+pub(crate) const DLL: u8 = 0;
+/// The high byte of the divisor latch, which is accessible if [`Lcr::DLAB`] is set.
+pub(crate) const DLM: u8 = 1;
+
+/// The number of the registers.
+pub(crate) const NR_REGS: u8 = 8;
+
+bitflags! {
+    /// The interrupt enable register.
+    pub(crate) struct Ier: u8 {
+        /// The received data available interrupt.
+        const RDI   = 1 << 0;
+        /// The transmitter holding register empty interrupt.
+        const THRI  = 1 << 1;
+        /// The receiver line status interrupt.
+        const RLSI  = 1 << 2;
+        /// The modem status interrupt.
+        const MSI   = 1 << 3;
+    }
+}
+
+bitflags! {
+    /// The FIFO control register.
+    pub(crate) struct Fcr: u8 {
+        const ENABLE        = 1 << 0;
+        const CLEAR_RCVR    = 1 << 1;
+        const CLEAR_XMIT    = 1 << 2;
+        /// Enables the 64-byte FIFOs of 16750, which is writable only if [`Lcr::DLAB`] is set.
+        const ENABLE_64     = 1 << 5;
+        /// The receiver FIFO triggers an interrupt when 8 bytes are received.
+        const TRIGGER_8     = 2 << 6;
+    }
+}
+
+/// The bit of the interrupt identification register indicating the 64-byte FIFOs are enabled.
+pub(crate) const IIR_64_BYTE_FIFO: u8 = 1 << 5;
+
+bitflags! {
+    /// The line control register.
+    pub(crate) struct Lcr: u8 {
+        const WLEN5     = 0;
+        const WLEN6     = 1;
+        const WLEN7     = 2;
+        const WLEN8     = 3;
+        /// Two stop bits (or 1.5 stop bits for 5-bit words).
+        const STOP      = 1 << 2;
+        const PARITY    = 1 << 3;
+        const EPAR      = 1 << 4;
+        /// The stick parity.
+        const SPAR      = 1 << 5;
+        const SBC       = 1 << 6;
+        /// The divisor latch access bit.
+        const DLAB      = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// The modem control register.
+    pub(crate) struct Mcr: u8 {
+        const DTR   = 1 << 0;
+        const RTS   = 1 << 1;
+        const OUT1  = 1 << 2;
+        /// Gates the interrupt output of the UART on PC-compatible boards.
+        const OUT2  = 1 << 3;
+        const LOOP  = 1 << 4;
+    }
+}
+
+bitflags! {
+    /// The line status register.
+    pub(crate) struct Lsr: u8 {
+        /// The data ready.
+        const DR    = 1 << 0;
+        /// The overrun error.
+        const OE    = 1 << 1;
+        /// The parity error.
+        const PE    = 1 << 2;
+        /// The framing error.
+        const FE    = 1 << 3;
+        /// The break interrupt.
+        const BI    = 1 << 4;
+        /// The transmitter holding register is empty.
+        const THRE  = 1 << 5;
+        /// The transmitter is empty.
+        const TEMT  = 1 << 6;
+
+        const RX_ERRORS = Self::PE.bits | Self::FE.bits | Self::BI.bits;
+    }
+}
+
+bitflags! {
+    /// The modem status register.
+    pub(crate) struct Msr: u8 {
+        /// The delta of the clear to send.
+        const DCTS  = 1 << 0;
+        const DDSR  = 1 << 1;
+        const TERI  = 1 << 2;
+        const DDCD  = 1 << 3;
+        /// The clear to send.
+        const CTS   = 1 << 4;
+        const DSR   = 1 << 5;
+        const RI    = 1 << 6;
+        const DCD   = 1 << 7;
+    }
+}
+
+/// The access to the registers of a UART.
+pub enum UartIo {
+    /// The registers are in the I/O port space.
+    #[cfg(target_arch = "x86_64")]
+    Pio([IoPort<u8, ReadWriteAccess>; NR_REGS as usize]),
+    /// The registers are in a PCI BAR starting from `offset`, where each register occupies one
+    /// byte.
+    Bar {
+        /// The BAR of the registers.
+        bar: Bar,
+        /// The offset of the first register in the BAR.
+        offset: usize,
+    },
+    /// The registers are memory-mapped, where each register occupies `1 << reg_shift` bytes.
+    Mmio {
+        /// The memory of the registers.
+        io_mem: IoMem,
+        /// The log2 of the distance between two adjacent registers in bytes.
+        reg_shift: u8,
+    },
+    /// The registers are emulated for testing.
+    #[cfg(ktest)]
+    Mock(alloc::sync::Arc<mock::MockUart>),
+}
+
+impl UartIo {
+    /// Acquires the I/O ports starting from `base`.
+    ///
+    /// This method fails if some of the ports are used by others (e.g., the early console).
+    #[cfg(target_arch = "x86_64")]
+    pub fn acquire_pio(base: u16) -> ostd::Result<Self> {
+        let mut ports = alloc::vec::Vec::with_capacity(NR_REGS as usize);
+        for reg in 0..NR_REGS {
+            ports.push(IoPort::acquire(base + reg as u16)?);
+        }
+        Ok(Self::Pio(ports.try_into().unwrap()))
+    }
+
+    pub(crate) fn read(&self, reg: u8) -> u8 {
+        // The offsets are checked when the access is created, so the reads cannot fail.
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Self::Pio(ports) => ports[reg as usize].read(),
+            Self::Bar { bar, offset } => bar.read_once(offset + reg as usize).unwrap(),
+            Self::Mmio { io_mem, reg_shift } => {
+                let offset = (reg as usize) << reg_shift;
+                if *reg_shift >= 2 {
+                    io_mem.read_once::<u32>(offset).unwrap() as u8
+                } else {
+                    io_mem.read_once::<u8>(offset).unwrap()
+                }
+            }
+            #[cfg(ktest)]
+            Self::Mock(uart) => uart.read(reg),
+        }
+    }
+
+    pub(crate) fn write(&self, reg: u8, value: u8) {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Self::Pio(ports) => ports[reg as usize].write(value),
+            Self::Bar { bar, offset } => bar.write_once(offset + reg as usize, value).unwrap(),
+            Self::Mmio { io_mem, reg_shift } => {
+                let offset = (reg as usize) << reg_shift;
+                if *reg_shift >= 2 {
+                    io_mem.write_once(offset, &(value as u32)).unwrap();
+                } else {
+                    io_mem.write_once(offset, &value).unwrap();
+                }
+            }
+            #[cfg(ktest)]
+            Self::Mock(uart) => uart.write(reg, value),
+        }
+    }
+
+    /// Returns the length of the register region required by the access.
+    pub(crate) fn region_len(reg_shift: u8) -> usize {
+        (NR_REGS as usize) << reg_shift
+    }
+}
+
+impl Debug for UartIo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Self::Pio(ports) => f
+                .debug_struct("Pio")
+                .field("base", &ports[0].port())
+                .finish(),
+            Self::Bar { bar, offset } => f
+                .debug_struct("Bar")
+                .field("bar", bar)
+                .field("offset", offset)
+                .finish(),
+            Self::Mmio { io_mem, reg_shift } => f
+                .debug_struct("Mmio")
+                .field("paddr", &io_mem.paddr())
+                .field("reg_shift", reg_shift)
+                .finish(),
+            #[cfg(ktest)]
+            Self::Mock(_) => f.debug_struct("Mock").finish(),
+        }
+    }
+}
+
+/// The types of the 8250-compatible UARTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartType {
+    /// 8250 without the scratch register.
+    U8250,
+    /// 16450 with the scratch register.
+    U16450,
+    /// 16550 whose FIFOs are broken.
+    U16550,
+    /// 16550A with the 16-byte FIFOs.
+    U16550A,
+    /// 16750 with the 64-byte FIFOs.
+    U16750,
+}
+
+impl UartType {
+    /// Detects the type of the UART.
+    ///
+    /// Returns `None` if there is no UART behind the registers.
+    pub(crate) fn detect(io: &UartIo) -> Option<Self> {
+        // Check that the interrupt enable register is present. Its upper bits are always zeros.
+        let saved_ier = io.read(IER);
+        io.write(IER, 0);
+        let ier_zero = io.read(IER);
+        io.write(IER, 0x0f);
+        let ier_full = io.read(IER);
+        io.write(IER, saved_ier);
+        if ier_zero & 0x0f != 0 || ier_full & 0x0f != 0x0f {
+            return None;
+        }
+
+        let saved_scr = io.read(SCR);
+        io.write(SCR, 0xa5);
+        let has_scratch = io.read(SCR) == 0xa5;
+        io.write(SCR, saved_scr);
+
+        io.write(IIR_FCR, Fcr::ENABLE.bits());
+        // The upper two bits of the interrupt identification register tell the FIFO state.
+        let uart_type = match io.read(IIR_FCR) >> 6 {
+            0 if has_scratch => Self::U16450,
+            0 => Self::U8250,
+            1 | 2 => Self::U16550,
+            _ => {
+                // The 64-byte FIFOs can only be enabled with the divisor latch accessible.
+                let lcr = io.read(LCR);
+                io.write(LCR, lcr | Lcr::DLAB.bits());
+                io.write(IIR_FCR, (Fcr::ENABLE | Fcr::ENABLE_64).bits());
+                let iir = io.read(IIR_FCR);
+                io.write(LCR, lcr);
+                if iir & IIR_64_BYTE_FIFO != 0 {
+                    Self::U16750
+                } else {
+                    Self::U16550A
+                }
+            }
+        };
+        io.write(IIR_FCR, 0);
+
+        Some(uart_type)
+    }
+
+    /// Returns the name of the type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::U8250 => "8250",
+            Self::U16450 => "16450",
+            Self::U16550 => "16550",
+            Self::U16550A => "16550A",
+            Self::U16750 => "16750",
+        }
+    }
+
+    /// Returns the size of the FIFOs in bytes.
+    pub fn fifo_size(&self) -> usize {
+        match self {
+            Self::U8250 | Self::U16450 | Self::U16550 => 1,
+            Self::U16550A => 16,
+            Self::U16750 => 64,
+        }
+    }
+
+    /// Returns the value of the FIFO control register to enable the FIFOs.
+    pub(crate) fn fcr(&self) -> Fcr {
+        match self {
+            Self::U8250 | Self::U16450 | Self::U16550 => Fcr::empty(),
+            Self::U16550A => Fcr::ENABLE | Fcr::TRIGGER_8,
+            Self::U16750 => Fcr::ENABLE | Fcr::ENABLE_64 | Fcr::TRIGGER_8,
+        }
+    }
+}
+
+#[cfg(ktest)]
+pub(crate) mod mock {
+    //! An emulated 16550A UART for testing.
+
+    use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+
+    use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+    use super::*;
+
+    /// A 16550A UART whose transmitter is always ready.
+    ///
+    /// The received bytes are injected by [`MockUart::receive`], and the transmitted bytes are
+    /// collected by [`MockUart::take_sent`].
+    pub(crate) struct MockUart {
+        regs: SpinLock<MockRegs, LocalIrqDisabled>,
+    }
+
+    #[derive(Default)]
+    struct MockRegs {
+        ier: u8,
+        fcr: u8,
+        lcr: u8,
+        mcr: u8,
+        msr: u8,
+        scr: u8,
+        divisor: u16,
+        /// The received bytes with their errors.
+        rx: VecDeque<(u8, Lsr)>,
+        tx: Vec<u8>,
+    }
+
+    impl MockUart {
+        pub(crate) fn new() -> Arc<Self> {
+            Arc::new(Self {
+                regs: SpinLock::new(MockRegs::default()),
+            })
+        }
+
+        /// Makes the bytes arrive with the errors.
+        pub(crate) fn receive(&self, bytes: &[u8], errors: Lsr) {
+            let mut regs = self.regs.lock();
+            regs.rx.extend(bytes.iter().map(|byte| (*byte, errors)));
+        }
+
+        /// Takes the bytes that have been transmitted.
+        pub(crate) fn take_sent(&self) -> Vec<u8> {
+            core::mem::take(&mut self.regs.lock().tx)
+        }
+
+        pub(crate) fn set_cts(&self, cts: bool) {
+            self.regs.lock().msr = if cts { Msr::CTS.bits() } else { 0 };
+        }
+
+        pub(crate) fn divisor(&self) -> u16 {
+            self.regs.lock().divisor
+        }
+
+        pub(crate) fn lcr(&self) -> Lcr {
+            Lcr::from_bits_truncate(self.regs.lock().lcr)
+        }
+
+        pub(crate) fn mcr(&self) -> Mcr {
+            Mcr::from_bits_truncate(self.regs.lock().mcr)
+        }
+
+        pub(crate) fn ier(&self) -> Ier {
+            Ier::from_bits_truncate(self.regs.lock().ier)
+        }
+
+        pub(super) fn read(&self, reg: u8) -> u8 {
+            let mut regs = self.regs.lock();
+            let is_dlab = regs.lcr & Lcr::DLAB.bits() != 0;
+            match reg {
+                DLL if is_dlab => regs.divisor.to_le_bytes()[0],
+                DLM if is_dlab => regs.divisor.to_le_bytes()[1],
+                RBR_THR => regs.rx.pop_front().map_or(0, |(byte, _)| byte),
+                IER => regs.ier,
+                // No interrupt is pending, and the upper bits tell whether the FIFOs are enabled.
+                IIR_FCR if regs.fcr & Fcr::ENABLE.bits() != 0 => 0xc1,
+                IIR_FCR => 0x01,
+                LCR => regs.lcr,
+                MCR => regs.mcr,
+                LSR => {
+                    let mut lsr = Lsr::THRE | Lsr::TEMT;
+                    if let Some((_, errors)) = regs.rx.front() {
+                        lsr |= Lsr::DR | *errors;
+                    }
+                    lsr.bits()
+                }
+                MSR => regs.msr,
+                SCR => regs.scr,
+                _ => unreachable!(),
+            }
+        }
+
+        pub(super) fn write(&self, reg: u8, value: u8) {
+            let mut regs = self.regs.lock();
+            let is_dlab = regs.lcr & Lcr::DLAB.bits() != 0;
+            match reg {
+                DLL if is_dlab => regs.divisor = (regs.divisor & 0xff00) | value as u16,
+                DLM if is_dlab => regs.divisor = (regs.divisor & 0x00ff) | (value as u16) << 8,
+                RBR_THR => regs.tx.push(value),
+                IER => regs.ier = value & 0x0f,
+                // The 64-byte FIFOs of 16750 are not emulated.
+                IIR_FCR => regs.fcr = value & !Fcr::ENABLE_64.bits(),
+                LCR => regs.lcr = value,
+                MCR => regs.mcr = value,
+                SCR => regs.scr = value,
+                LSR | MSR => (),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of sound cards.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use log::{info, warn};
+use ostd::sync::Mutex;
+
+use crate::PcmStream;
+
+/// The maximum number of the sound cards, which is the same as Linux's `SNDRV_CARDS`.
+pub const MAX_CARDS: u32 = 8;
+
+/// A registered sound card.
+#[derive(Debug)]
+pub struct SoundCard {
+    index: u32,
+    name: String,
+    pcms: Vec<Arc<dyn PcmStream>>,
+}
+
+impl SoundCard {
+    /// Returns the index of the card, which is `x` in `pcmCxDyp`.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the name of the card.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the playback PCM streams, whose indexes are `y` in `pcmCxDyp`.
+    pub fn pcms(&self) -> &[Arc<dyn PcmStream>] {
+        &self.pcms
+    }
+}
+
+static CARDS: Mutex<Vec<Arc<SoundCard>>> = Mutex::new(Vec::new());
+
+/// Registers a sound card with its playback PCM streams.
+///
+/// Returns `None` if there are too many cards.
+pub fn register_card(name: String, pcms: Vec<Arc<dyn PcmStream>>) -> Option<Arc<SoundCard>> {
+    let mut cards = CARDS.lock();
+
+    let index = cards.len() as u32;
+    if index >= MAX_CARDS {
+        warn!("[Sound]: Too many sound cards, {} is ignored", name);
+        return None;
+    }
+
+    let card = Arc::new(SoundCard { index, name, pcms });
+    info!(
+        "[Sound]: Registered card {} ({}) with {} PCM streams",
+        index,
+        card.name,
+        card.pcms.len()
+    );
+
+    cards.push(card.clone());
+    Some(card)
+}
+
+/// Returns the card of the index.
+pub fn get_card(index: u32) -> Option<Arc<SoundCard>> {
+    CARDS.lock().get(index as usize).cloned()
+}
+
+/// Returns all the cards.
+pub fn all_cards() -> Vec<Arc<SoundCard>> {
+    CARDS.lock().clone()
+}
@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The sound subsystem of Asterinas.
+//!
+//! The subsystem follows the model of ALSA in Linux:
+//!  - A sound card ([`SoundCard`]) is registered by a driver, and it is identified by its index.
+//!  - Each card has some playback PCM streams ([`PcmStream`]), which are identified by their
+//!    indexes in the card (i.e., the PCM device numbers).
+//!  - A stream plays the audio data in a ring buffer that is divided into periods. The driver
+//!    reports each period that has been played ([`PcmListener`]), so the writer can refill it.
+//!
+//! Userspace accesses the streams via `/dev/snd/pcmCxDyp`, which is implemented by the kernel
+//! on top of [`get_card`].
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod card;
+
+use alloc::{sync::Weak, vec::Vec};
+use core::{fmt::Debug, ops::RangeInclusive};
+
+pub use card::{all_cards, get_card, register_card, SoundCard, MAX_CARDS};
+use ostd::mm::DmaStream;
+
+/// The format of the samples.
+///
+/// The values are the same as `SNDRV_PCM_FORMAT_*` of Linux. Only the little-endian linear
+/// formats are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PcmFormat {
+    S8 = 0,
+    U8 = 1,
+    S16Le = 2,
+    U16Le = 4,
+    /// The signed 24-bit samples in the low bits of 32-bit words.
+    S24Le = 6,
+    /// The unsigned 24-bit samples in the low bits of 32-bit words.
+    U24Le = 8,
+    S32Le = 10,
+    U32Le = 12,
+    /// The 32-bit IEEE 754 floating-point samples in the range of `[-1.0, 1.0]`.
+    FloatLe = 14,
+}
+
+impl PcmFormat {
+    /// All the formats.
+    pub const ALL: [Self; 9] = [
+        Self::S8,
+        Self::U8,
+        Self::S16Le,
+        Self::U16Le,
+        Self::S24Le,
+        Self::U24Le,
+        Self::S32Le,
+        Self::U32Le,
+        Self::FloatLe,
+    ];
+
+    /// Returns the format of the value, if it is supported.
+    pub fn from_raw(value: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| *format as u32 == value)
+    }
+
+    /// Returns the number of the significant bits of a sample.
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::S8 | Self::U8 => 8,
+            Self::S16Le | Self::U16Le => 16,
+            Self::S24Le | Self::U24Le => 24,
+            Self::S32Le | Self::U32Le | Self::FloatLe => 32,
+        }
+    }
+
+    /// Returns the number of the bits that a sample occupies in the memory.
+    pub fn physical_width(&self) -> u32 {
+        match self {
+            Self::S8 | Self::U8 => 8,
+            Self::S16Le | Self::U16Le => 16,
+            Self::S24Le | Self::U24Le | Self::S32Le | Self::U32Le | Self::FloatLe => 32,
+        }
+    }
+
+    /// Returns the sample of silence.
+    ///
+    /// The sample occupies the low [`Self::physical_width`] bits and is stored in little endian.
+    pub fn silence(&self) -> u32 {
+        match self {
+            Self::U8 | Self::U16Le | Self::U24Le | Self::U32Le => 1 << (self.width() - 1),
+            Self::S8 | Self::S16Le | Self::S24Le | Self::S32Le | Self::FloatLe => 0,
+        }
+    }
+}
+
+/// The capabilities of a PCM stream.
+#[derive(Debug, Clone)]
+pub struct PcmInfo {
+    /// The supported formats.
+    pub formats: Vec<PcmFormat>,
+    /// The supported rates in Hz, in ascending order.
+    pub rates: Vec<u32>,
+    /// The range of the number of the channels.
+    pub channels: RangeInclusive<u32>,
+    /// The range of the number of the periods in the buffer.
+    pub periods: RangeInclusive<u32>,
+    /// The maximum size of the buffer in bytes.
+    pub buffer_bytes_max: usize,
+}
+
+/// The parameters of a PCM stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmParams {
+    pub format: PcmFormat,
+    pub channels: u32,
+    /// The rate in Hz.
+    pub rate: u32,
+    /// The size of a period in bytes.
+    pub period_bytes: usize,
+    /// The size of the buffer in bytes, which is a multiple of the period size.
+    pub buffer_bytes: usize,
+}
+
+impl PcmParams {
+    /// Returns the number of the periods in the buffer.
+    pub fn periods(&self) -> usize {
+        self.buffer_bytes / self.period_bytes
+    }
+}
+
+/// A playback PCM stream.
+///
+/// The methods are called with the stream opened exclusively, so they are never called
+/// concurrently.
+pub trait PcmStream: Send + Sync + Debug {
+    /// Returns the capabilities of the stream.
+    fn info(&self) -> &PcmInfo;
+
+    /// Sets the parameters and the buffer, and makes the stream ready to start.
+    ///
+    /// The parameters are supported by the capabilities, and the size of `buffer` is
+    /// `params.buffer_bytes`. If the stream has been prepared, it is stopped first.
+    fn prepare(&self, params: &PcmParams, buffer: &DmaStream) -> Result<(), SoundError>;
+
+    /// Starts playing the periods from the beginning of the buffer.
+    ///
+    /// [`PcmListener::on_period_elapsed`] is called after each period is played. The period is
+    /// played again in the next round of the buffer if the listener returns `true`.
+    fn start(&self) -> Result<(), SoundError>;
+
+    /// Stops the stream, after which the buffer is no longer accessed.
+    ///
+    /// The stream should be prepared again before it is started. This method does nothing if
+    /// the stream has not been prepared.
+    fn stop(&self) -> Result<(), SoundError>;
+
+    /// Sets the listener of the periods.
+    fn set_listener(&self, listener: Weak<dyn PcmListener>);
+}
+
+/// The listener of the periods of a PCM stream.
+pub trait PcmListener: Send + Sync {
+    /// Called in the interrupt context when a period has been played.
+    ///
+    /// Returns whether the stream should keep running.
+    fn on_period_elapsed(&self) -> bool;
+}
+
+/// The errors of sound operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundError {
+    /// The operation or the parameters are not supported.
+    NotSupported,
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// Other errors reported by the device.
+    Io,
+}
@@ -0,0 +1,480 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of SPI controllers, devices, and drivers.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use log::{info, warn};
+use ostd::sync::Mutex;
+
+use crate::{queue::MessageQueue, SpiController, SpiDevice, SpiError, SpiMode};
+
+/// An SPI driver.
+pub trait SpiDriver: Send + Sync + Debug {
+    /// Returns the names of the devices that the driver can drive.
+    fn id_table(&self) -> &[&str];
+
+    /// Probes a device whose name is in the ID table.
+    ///
+    /// If this method fails, the device can be probed by other drivers.
+    fn probe(&self, device: &Arc<SpiDevice>) -> Result<(), SpiError>;
+}
+
+/// The information to instantiate an SPI device.
+#[derive(Debug, Clone)]
+pub struct SpiBoardInfo {
+    /// The name of the device, which is matched against the ID tables of the drivers.
+    pub name: String,
+    /// The chip select of the device.
+    pub chip_select: u16,
+    /// The mode of the device.
+    pub mode: SpiMode,
+    /// The maximum clock frequency of the device, or zero if it is unknown.
+    pub max_speed_hz: u32,
+}
+
+/// Registers a controller and returns its bus number.
+///
+/// The devices declared for the bus number are instantiated and probed.
+pub fn register_controller(controller: Arc<dyn SpiController>) -> u32 {
+    let mut bus = SPI_BUS.lock();
+
+    let bus_nr = bus.next_bus_nr;
+    bus.next_bus_nr += 1;
+    info!("[SPI]: Registered spi{}: {}", bus_nr, controller.name());
+    let queue = Arc::new(MessageQueue::new(controller.clone()));
+    bus.controllers
+        .insert(bus_nr, ControllerEntry { controller, queue });
+
+    let board_infos = bus
+        .board_infos
+        .extract_if(.., |(info_bus_nr, _)| *info_bus_nr == bus_nr)
+        .collect::<Vec<_>>();
+    for (_, info) in board_infos {
+        bus.add_device(bus_nr, info);
+    }
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+
+    bus_nr
+}
+
+/// Returns the controller of the bus number.
+pub fn get_controller(bus_nr: u32) -> Option<Arc<dyn SpiController>> {
+    SPI_BUS
+        .lock()
+        .controllers
+        .get(&bus_nr)
+        .map(|entry| entry.controller.clone())
+}
+
+/// Returns all the controllers with their bus numbers.
+pub fn all_controllers() -> Vec<(u32, Arc<dyn SpiController>)> {
+    SPI_BUS
+        .lock()
+        .controllers
+        .iter()
+        .map(|(bus_nr, entry)| (*bus_nr, entry.controller.clone()))
+        .collect()
+}
+
+/// Returns all the devices.
+pub fn all_devices() -> Vec<Arc<SpiDevice>> {
+    SPI_BUS
+        .lock()
+        .devices
+        .iter()
+        .map(|entry| entry.device.clone())
+        .collect()
+}
+
+/// Declares a device on the bus.
+///
+/// The device is instantiated once the controller of the bus is registered.
+pub fn register_board_info(bus_nr: u32, info: SpiBoardInfo) {
+    let mut bus = SPI_BUS.lock();
+
+    if !bus.controllers.contains_key(&bus_nr) {
+        bus.board_infos.push((bus_nr, info));
+        return;
+    }
+    bus.add_device(bus_nr, info);
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+}
+
+/// Registers a driver.
+///
+/// The devices that are not driven yet are probed by the driver.
+pub fn register_driver(driver: Arc<dyn SpiDriver>) {
+    let mut bus = SPI_BUS.lock();
+    bus.drivers.push(driver);
+
+    let probes = bus.take_pending_probes();
+    drop(bus);
+    do_probes(probes);
+}
+
+static SPI_BUS: Mutex<SpiBus> = Mutex::new(SpiBus::new());
+
+struct SpiBus {
+    controllers: BTreeMap<u32, ControllerEntry>,
+    next_bus_nr: u32,
+    board_infos: Vec<(u32, SpiBoardInfo)>,
+    devices: Vec<DeviceEntry>,
+    drivers: Vec<Arc<dyn SpiDriver>>,
+}
+
+struct ControllerEntry {
+    controller: Arc<dyn SpiController>,
+    queue: Arc<MessageQueue>,
+}
+
+struct DeviceEntry {
+    device: Arc<SpiDevice>,
+    state: DeviceState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeviceState {
+    Unbound,
+    Probing,
+    Bound,
+}
+
+/// A device and the drivers to probe it in order.
+type PendingProbe = (Arc<SpiDevice>, Vec<Arc<dyn SpiDriver>>);
+
+impl SpiBus {
+    const fn new() -> Self {
+        Self {
+            controllers: BTreeMap::new(),
+            next_bus_nr: 0,
+            board_infos: Vec::new(),
+            devices: Vec::new(),
+            drivers: Vec::new(),
+        }
+    }
+
+    fn add_device(&mut self, bus_nr: u32, info: SpiBoardInfo) {
+        let entry = self.controllers.get(&bus_nr).unwrap();
+        if info.chip_select >= entry.controller.num_chip_selects() {
+            warn!(
+                "[SPI]: Invalid chip select {} for {}",
+                info.chip_select, info.name
+            );
+            return;
+        }
+        if self.devices.iter().any(|entry| {
+            entry.device.bus_nr() == bus_nr && entry.device.chip_select() == info.chip_select
+        }) {
+            warn!(
+                "[SPI]: Chip select {} on spi{} is already used",
+                info.chip_select, bus_nr
+            );
+            return;
+        }
+
+        let device = SpiDevice::new(
+            bus_nr,
+            entry.controller.clone(),
+            entry.queue.clone(),
+            info.name,
+            info.chip_select,
+        );
+        if let Err(err) = device.setup(info.mode, 0, info.max_speed_hz) {
+            warn!(
+                "[SPI]: Failed to set up {} on spi{}: {:?}",
+                device.name(),
+                bus_nr,
+                err
+            );
+            return;
+        }
+
+        self.devices.push(DeviceEntry {
+            device: Arc::new(device),
+            state: DeviceState::Unbound,
+        });
+    }
+
+    /// Finds the unbound devices that match some drivers and marks them as being probed.
+    fn take_pending_probes(&mut self) -> Vec<PendingProbe> {
+        let mut probes = Vec::new();
+
+        for entry in self.devices.iter_mut() {
+            if entry.state != DeviceState::Unbound {
+                continue;
+            }
+
+            let drivers = self
+                .drivers
+                .iter()
+                .filter(|driver| driver.id_table().contains(&entry.device.name()))
+                .cloned()
+                .collect::<Vec<_>>();
+            if drivers.is_empty() {
+                continue;
+            }
+
+            entry.state = DeviceState::Probing;
+            probes.push((entry.device.clone(), drivers));
+        }
+
+        probes
+    }
+
+    fn set_device_state(&mut self, device: &Arc<SpiDevice>, state: DeviceState) {
+        let entry = self
+            .devices
+            .iter_mut()
+            .find(|entry| Arc::ptr_eq(&entry.device, device))
+            .unwrap();
+        entry.state = state;
+    }
+}
+
+/// Probes the devices without holding the lock, since probing may perform slow transfers.
+fn do_probes(probes: Vec<PendingProbe>) {
+    for (device, drivers) in probes {
+        let is_bound = drivers.iter().any(|driver| match driver.probe(&device) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(
+                    "[SPI]: Failed to probe {} at chip select {} on spi{}: {:?}",
+                    device.name(),
+                    device.chip_select(),
+                    device.bus_nr(),
+                    err
+                );
+                false
+            }
+        });
+
+        let state = if is_bound {
+            DeviceState::Bound
+        } else {
+            DeviceState::Unbound
+        };
+        SPI_BUS.lock().set_device_state(&device, state);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::{string::ToString, vec};
+
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{SpiDeviceConfig, SpiMessage, SpiTransfer};
+
+    /// The byte that makes the mock controller fail a transfer.
+    const BAD_BYTE: u8 = 0xff;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Cs(u16, bool),
+        Transfer(usize),
+    }
+
+    /// A controller with the data looped back from the output to the input.
+    #[derive(Debug)]
+    struct MockController {
+        events: Mutex<Vec<Event>>,
+    }
+
+    impl MockController {
+        fn take_events(&self) -> Vec<Event> {
+            core::mem::take(&mut *self.events.lock())
+        }
+    }
+
+    impl SpiController for MockController {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn num_chip_selects(&self) -> u16 {
+            2
+        }
+
+        fn mode_bits(&self) -> SpiMode {
+            SpiMode::MODE_3 | SpiMode::CS_HIGH
+        }
+
+        fn bits_per_word_mask(&self) -> u32 {
+            (1 << 7) | (1 << 15)
+        }
+
+        fn max_speed_hz(&self) -> u32 {
+            1_000_000
+        }
+
+        fn prepare_message(&self, _config: &SpiDeviceConfig) -> Result<(), SpiError> {
+            Ok(())
+        }
+
+        fn set_cs(&self, config: &SpiDeviceConfig, is_asserted: bool) {
+            self.events
+                .lock()
+                .push(Event::Cs(config.chip_select, is_asserted));
+        }
+
+        fn transfer_one(
+            &self,
+            _config: &SpiDeviceConfig,
+            transfer: &mut SpiTransfer,
+        ) -> Result<(), SpiError> {
+            let tx_buf = transfer.tx_buf.clone().unwrap_or(vec![0; transfer.len]);
+            if tx_buf.contains(&BAD_BYTE) {
+                return Err(SpiError::Io);
+            }
+            if let Some(rx_buf) = transfer.rx_buf.as_mut() {
+                rx_buf.copy_from_slice(&tx_buf);
+            }
+            self.events.lock().push(Event::Transfer(transfer.len));
+            Ok(())
+        }
+    }
+
+    /// A driver that binds all the devices with its name.
+    #[derive(Debug)]
+    struct MockDriver {
+        devices: Mutex<Vec<Arc<SpiDevice>>>,
+    }
+
+    impl SpiDriver for MockDriver {
+        fn id_table(&self) -> &[&str] {
+            &["mock-flash"]
+        }
+
+        fn probe(&self, device: &Arc<SpiDevice>) -> Result<(), SpiError> {
+            self.devices.lock().push(device.clone());
+            Ok(())
+        }
+    }
+
+    fn register_mock() -> (Arc<MockController>, u32) {
+        let controller = Arc::new(MockController {
+            events: Mutex::new(Vec::new()),
+        });
+        let bus_nr = register_controller(controller.clone());
+        (controller, bus_nr)
+    }
+
+    fn board_info(chip_select: u16, mode: SpiMode) -> SpiBoardInfo {
+        SpiBoardInfo {
+            name: "mock-flash".to_string(),
+            chip_select,
+            mode,
+            max_speed_hz: 2_000_000,
+        }
+    }
+
+    fn find_device(bus_nr: u32, chip_select: u16) -> Option<Arc<SpiDevice>> {
+        all_devices()
+            .into_iter()
+            .find(|device| device.bus_nr() == bus_nr && device.chip_select() == chip_select)
+    }
+
+    #[ktest]
+    fn register_and_transfer() {
+        let driver = Arc::new(MockDriver {
+            devices: Mutex::new(Vec::new()),
+        });
+        register_driver(driver.clone());
+        let (controller, bus_nr) = register_mock();
+        assert_eq!(get_controller(bus_nr).unwrap().name(), "mock");
+
+        register_board_info(bus_nr, board_info(0, SpiMode::MODE_3));
+        register_board_info(bus_nr, board_info(1, SpiMode::MODE_0));
+        // The driver may also bind the devices on the buses of other tests.
+        let devices = driver
+            .devices
+            .lock()
+            .iter()
+            .filter(|device| device.bus_nr() == bus_nr)
+            .cloned()
+            .collect::<Vec<_>>();
+        let [device0, device1] = devices.try_into().unwrap();
+        // The frequency is limited by the controller.
+        let config = device0.config();
+        assert_eq!((config.mode, config.bits_per_word), (SpiMode::MODE_3, 8));
+        assert_eq!(config.max_speed_hz, 1_000_000);
+
+        let mut msg = SpiMessage::new(vec![
+            SpiTransfer::new_write(vec![1, 2]),
+            SpiTransfer::new_duplex(vec![3, 4, 5]),
+        ]);
+        device0.sync(&mut msg).unwrap();
+        assert_eq!(msg.actual_length, 5);
+        assert_eq!(msg.transfers[1].rx_buf.as_deref(), Some(&[3, 4, 5][..]));
+        assert_eq!(msg.transfers[1].speed_hz, 1_000_000);
+        assert_eq!(
+            controller.take_events(),
+            [
+                Event::Cs(0, true),
+                Event::Transfer(2),
+                Event::Transfer(3),
+                Event::Cs(0, false)
+            ]
+        );
+
+        // The chip select is kept asserted until a message to another device.
+        let mut transfer = SpiTransfer::new_write(vec![6]);
+        transfer.cs_change = true;
+        device0.sync(&mut SpiMessage::new(vec![transfer])).unwrap();
+        device1.write(&[7]).unwrap();
+        assert_eq!(
+            controller.take_events(),
+            [
+                Event::Cs(0, true),
+                Event::Transfer(1),
+                Event::Cs(0, false),
+                Event::Cs(1, true),
+                Event::Transfer(1),
+                Event::Cs(1, false)
+            ]
+        );
+    }
+
+    #[ktest]
+    fn error_paths() {
+        let (controller, bus_nr) = register_mock();
+
+        // Devices with invalid chip selects or unsupported modes are not instantiated.
+        register_board_info(bus_nr, board_info(2, SpiMode::MODE_0));
+        register_board_info(bus_nr, board_info(1, SpiMode::LSB_FIRST));
+        assert!(find_device(bus_nr, 1).is_none());
+
+        register_board_info(bus_nr, board_info(0, SpiMode::MODE_0));
+        let device = find_device(bus_nr, 0).unwrap();
+        assert_eq!(
+            device.setup(SpiMode::MODE_0, 12, 0),
+            Err(SpiError::NotSupported)
+        );
+
+        let mut invalid_transfer = SpiTransfer::new_write(vec![1, 2]);
+        invalid_transfer.len = 1;
+        let mut odd_words = SpiTransfer::new_write(vec![1, 2, 3]);
+        odd_words.bits_per_word = 16;
+        for transfers in [vec![], vec![invalid_transfer], vec![odd_words]] {
+            let mut msg = SpiMessage::new(transfers);
+            assert_eq!(device.sync(&mut msg), Err(SpiError::InvalidArgs));
+        }
+        assert!(controller.take_events().is_empty());
+
+        // The chip select is deasserted if a transfer fails.
+        assert_eq!(device.write(&[BAD_BYTE]), Err(SpiError::Io));
+        assert_eq!(
+            controller.take_events(),
+            [Event::Cs(0, true), Event::Cs(0, false)]
+        );
+    }
+}
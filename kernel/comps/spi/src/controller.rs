@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SPI controllers.
+
+use core::fmt::Debug;
+
+use bitflags::bitflags;
+
+use crate::{SpiDeviceConfig, SpiError, SpiTransfer};
+
+/// An SPI controller, i.e., the master of an SPI bus.
+///
+/// The controllers only perform the transfers one by one. The messages, which consist of
+/// transfers, are processed by the subsystem, which asserts and deasserts the chip selects
+/// around the transfers.
+pub trait SpiController: Send + Sync + Debug {
+    /// Returns the name of the controller.
+    fn name(&self) -> &str;
+
+    /// Returns the number of the chip selects.
+    fn num_chip_selects(&self) -> u16;
+
+    /// Returns the mode bits supported by the controller.
+    fn mode_bits(&self) -> SpiMode;
+
+    /// Returns the supported numbers of bits per word.
+    ///
+    /// Bit `n - 1` is set if `n` bits per word are supported.
+    fn bits_per_word_mask(&self) -> u32;
+
+    /// Returns the maximum clock frequency.
+    fn max_speed_hz(&self) -> u32;
+
+    /// Prepares the controller to transfer a message to the device.
+    ///
+    /// This method is called before the chip select is asserted, so the clock mode and the
+    /// polarity of the chip select can be set here.
+    fn prepare_message(&self, config: &SpiDeviceConfig) -> Result<(), SpiError>;
+
+    /// Asserts or deasserts the chip select of the device.
+    fn set_cs(&self, config: &SpiDeviceConfig, is_asserted: bool);
+
+    /// Performs a transfer.
+    ///
+    /// The speed and the bits per word of the transfer have been resolved against the device and
+    /// validated against the controller.
+    fn transfer_one(
+        &self,
+        config: &SpiDeviceConfig,
+        transfer: &mut SpiTransfer,
+    ) -> Result<(), SpiError>;
+}
+
+bitflags! {
+    /// The mode of an SPI device.
+    ///
+    /// The values are the same as Linux, so they can be exchanged with userspace directly.
+    pub struct SpiMode: u32 {
+        /// The clock phase, i.e., the data are sampled on the trailing edge of the clock.
+        const CPHA           = 0x0001;
+        /// The clock polarity, i.e., the clock is high when idle.
+        const CPOL           = 0x0002;
+        /// The chip select is active high.
+        const CS_HIGH        = 0x0004;
+        /// The least significant bit is transferred first.
+        const LSB_FIRST      = 0x0008;
+        /// The data are written and read on the same wire.
+        const THREE_WIRE     = 0x0010;
+        /// The data are looped back from the output to the input.
+        const LOOP           = 0x0020;
+        /// There is no chip select.
+        const NO_CS          = 0x0040;
+        /// The device pulls the input low to pause.
+        const READY          = 0x0080;
+        const TX_DUAL        = 0x0100;
+        const TX_QUAD        = 0x0200;
+        const RX_DUAL        = 0x0400;
+        const RX_QUAD        = 0x0800;
+        /// The chip select is toggled between words.
+        const CS_WORD        = 0x1000;
+        const TX_OCTAL       = 0x2000;
+        const RX_OCTAL       = 0x4000;
+        /// The output is high impedance when the data are read in three-wire mode.
+        const THREE_WIRE_HIZ = 0x8000;
+
+        const MODE_0 = 0;
+        const MODE_1 = Self::CPHA.bits;
+        const MODE_2 = Self::CPOL.bits;
+        const MODE_3 = Self::CPOL.bits | Self::CPHA.bits;
+    }
+}
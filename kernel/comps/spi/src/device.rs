@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SPI devices.
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+
+use ostd::sync::Mutex;
+
+use crate::{
+    queue::{is_bits_per_word_supported, MessageQueue},
+    SpiController, SpiError, SpiMessage, SpiMode, SpiTransfer,
+};
+
+/// The configuration of an SPI device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiDeviceConfig {
+    pub chip_select: u16,
+    pub mode: SpiMode,
+    pub bits_per_word: u8,
+    pub max_speed_hz: u32,
+}
+
+/// An SPI device, i.e., a device selected by some chip select of a controller.
+#[derive(Debug)]
+pub struct SpiDevice {
+    bus_nr: u32,
+    controller: Arc<dyn SpiController>,
+    queue: Arc<MessageQueue>,
+    name: String,
+    config: Mutex<SpiDeviceConfig>,
+}
+
+impl SpiDevice {
+    pub(crate) fn new(
+        bus_nr: u32,
+        controller: Arc<dyn SpiController>,
+        queue: Arc<MessageQueue>,
+        name: String,
+        chip_select: u16,
+    ) -> Self {
+        let config = SpiDeviceConfig {
+            chip_select,
+            mode: SpiMode::MODE_0,
+            bits_per_word: 8,
+            max_speed_hz: controller.max_speed_hz(),
+        };
+
+        Self {
+            bus_nr,
+            controller,
+            queue,
+            name,
+            config: Mutex::new(config),
+        }
+    }
+
+    /// Returns the bus number of the controller.
+    pub fn bus_nr(&self) -> u32 {
+        self.bus_nr
+    }
+
+    pub fn controller(&self) -> &Arc<dyn SpiController> {
+        &self.controller
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn chip_select(&self) -> u16 {
+        self.config.lock().chip_select
+    }
+
+    pub fn config(&self) -> SpiDeviceConfig {
+        *self.config.lock()
+    }
+
+    /// Sets the mode, the bits per word, and the maximum clock frequency of the device.
+    ///
+    /// Zero bits per word means 8 bits. A zero frequency, or one that is higher than the
+    /// controller supports, means the maximum frequency of the controller.
+    pub fn setup(
+        &self,
+        mode: SpiMode,
+        bits_per_word: u8,
+        max_speed_hz: u32,
+    ) -> Result<(), SpiError> {
+        if !self.controller.mode_bits().contains(mode) {
+            return Err(SpiError::NotSupported);
+        }
+
+        let bits_per_word = if bits_per_word == 0 { 8 } else { bits_per_word };
+        if !is_bits_per_word_supported(self.controller.as_ref(), bits_per_word) {
+            return Err(SpiError::NotSupported);
+        }
+
+        let controller_max_speed_hz = self.controller.max_speed_hz();
+        let max_speed_hz = if max_speed_hz == 0 || max_speed_hz > controller_max_speed_hz {
+            controller_max_speed_hz
+        } else {
+            max_speed_hz
+        };
+
+        let mut config = self.config.lock();
+        config.mode = mode;
+        config.bits_per_word = bits_per_word;
+        config.max_speed_hz = max_speed_hz;
+        Ok(())
+    }
+
+    /// Transfers a message to the device.
+    ///
+    /// This method returns after the message is transferred.
+    pub fn sync(&self, msg: &mut SpiMessage) -> Result<(), SpiError> {
+        let config = self.config();
+        self.queue.transfer(&config, msg)
+    }
+
+    /// Writes the data to the device in a single transfer.
+    pub fn write(&self, buf: &[u8]) -> Result<(), SpiError> {
+        let mut msg = SpiMessage::new(vec![SpiTransfer::new_write(buf.to_vec())]);
+        self.sync(&mut msg)
+    }
+
+    /// Reads data from the device in a single transfer.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), SpiError> {
+        let mut msg = SpiMessage::new(vec![SpiTransfer::new_read(buf.len())]);
+        self.sync(&mut msg)?;
+        buf.copy_from_slice(msg.transfers[0].rx_buf.as_ref().unwrap());
+        Ok(())
+    }
+
+    /// Writes the data to the device and then reads data from it in a single message.
+    ///
+    /// This is the common pattern to read the registers of a device.
+    pub fn write_then_read(&self, tx_buf: &[u8], rx_buf: &mut [u8]) -> Result<(), SpiError> {
+        let mut transfers = Vec::with_capacity(2);
+        if !tx_buf.is_empty() {
+            transfers.push(SpiTransfer::new_write(tx_buf.to_vec()));
+        }
+        transfers.push(SpiTransfer::new_read(rx_buf.len()));
+
+        let mut msg = SpiMessage::new(transfers);
+        self.sync(&mut msg)?;
+        rx_buf.copy_from_slice(msg.transfers.last().unwrap().rx_buf.as_ref().unwrap());
+        Ok(())
+    }
+}
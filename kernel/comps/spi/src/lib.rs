@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SPI subsystem of Asterinas.
+//!
+//! The subsystem follows the model of Linux:
+//!  - A controller ([`SpiController`]) is the master of an SPI bus. Each controller is identified
+//!    by its bus number, which is assigned when the controller is registered.
+//!  - A device ([`SpiDevice`]) is selected by some chip select of a controller. Devices are
+//!    declared by [`register_board_info`], since SPI devices cannot be enumerated.
+//!  - A driver ([`SpiDriver`]) drives the devices whose names are in its ID table.
+//!
+//! The data are exchanged in messages ([`SpiMessage`]), each of which consists of some transfers
+//! ([`SpiTransfer`]) performed with the chip select asserted. The messages to a controller are
+//! queued and transferred one at a time.
+//!
+//! Userspace can access the devices driven by the `spidev` driver via `/dev/spidevB.C`, which is
+//! implemented by the kernel on top of [`register_driver`].
+#![no_std]
+#![deny(unsafe_code)]
+#![feature(extract_if)]
+
+extern crate alloc;
+
+mod bus;
+mod controller;
+mod device;
+mod message;
+mod queue;
+#[cfg(target_arch = "riscv64")]
+mod sifive;
+
+pub use bus::{
+    all_controllers, all_devices, get_controller, register_board_info, register_controller,
+    register_driver, SpiBoardInfo, SpiDriver,
+};
+use component::{init_component, ComponentInitError};
+pub use controller::{SpiController, SpiMode};
+pub use device::{SpiDevice, SpiDeviceConfig};
+pub use message::{SpiMessage, SpiTransfer};
+
+/// The errors of SPI operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiError {
+    /// The operation is not supported by the controller.
+    NotSupported,
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// The transfer is not completed in time.
+    Timeout,
+    /// Other errors reported by the controller.
+    Io,
+}
+
+#[init_component]
+fn spi_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    sifive::init();
+    Ok(())
+}
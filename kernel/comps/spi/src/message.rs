@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SPI messages and transfers.
+
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+/// An SPI transfer.
+///
+/// In each clock cycle, a bit is written and a bit is read at the same time. If there is no
+/// buffer to write, zeros are written. If there is no buffer to read, the read bits are dropped.
+#[derive(Debug, Default)]
+pub struct SpiTransfer {
+    /// The data to write, whose length must be `len`.
+    pub tx_buf: Option<Vec<u8>>,
+    /// The buffer to read into, whose length must be `len`.
+    pub rx_buf: Option<Vec<u8>>,
+    /// The number of bytes to transfer.
+    pub len: usize,
+    /// The clock frequency, or zero to use the one of the device.
+    pub speed_hz: u32,
+    /// The number of bits per word, or zero to use the one of the device.
+    pub bits_per_word: u8,
+    /// Whether to toggle the chip select after the transfer.
+    ///
+    /// For the last transfer of a message, this keeps the chip select asserted after the message.
+    pub cs_change: bool,
+    /// The delay after the transfer.
+    pub delay: Duration,
+}
+
+impl SpiTransfer {
+    /// Creates a transfer that writes the data.
+    pub fn new_write(tx_buf: Vec<u8>) -> Self {
+        Self {
+            len: tx_buf.len(),
+            tx_buf: Some(tx_buf),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a transfer that reads `len` bytes.
+    pub fn new_read(len: usize) -> Self {
+        Self {
+            rx_buf: Some(vec![0; len]),
+            len,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a transfer that writes the data and reads the same number of bytes.
+    pub fn new_duplex(tx_buf: Vec<u8>) -> Self {
+        Self {
+            len: tx_buf.len(),
+            rx_buf: Some(vec![0; tx_buf.len()]),
+            tx_buf: Some(tx_buf),
+            ..Default::default()
+        }
+    }
+}
+
+/// An SPI message, i.e., some transfers performed with the chip select asserted.
+#[derive(Debug, Default)]
+pub struct SpiMessage {
+    pub transfers: Vec<SpiTransfer>,
+    /// The number of the bytes transferred, which is set after the message is transferred.
+    pub actual_length: usize,
+}
+
+impl SpiMessage {
+    pub fn new(transfers: Vec<SpiTransfer>) -> Self {
+        Self {
+            transfers,
+            actual_length: 0,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The queues of the messages to the controllers.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::time::Duration;
+
+use ostd::{sync::Mutex, task::Task, timer::Jiffies};
+
+use crate::{SpiController, SpiDeviceConfig, SpiError, SpiMessage};
+
+/// The time for which the chip select is deasserted when it is toggled between transfers.
+const CS_CHANGE_DELAY: Duration = Duration::from_micros(10);
+
+/// The queue of the messages to a controller.
+///
+/// The messages wait on the lock of the queue, and are transferred one at a time in the context
+/// of their submitters.
+#[derive(Debug)]
+pub(crate) struct MessageQueue {
+    controller: Arc<dyn SpiController>,
+    /// The device whose chip select is kept asserted after its last message.
+    kept_cs: Mutex<Option<SpiDeviceConfig>>,
+}
+
+impl MessageQueue {
+    pub(crate) fn new(controller: Arc<dyn SpiController>) -> Self {
+        Self {
+            controller,
+            kept_cs: Mutex::new(None),
+        }
+    }
+
+    /// Transfers a message to the device.
+    ///
+    /// The unspecified speeds and bits per word of the transfers are resolved before the
+    /// message is transferred.
+    pub(crate) fn transfer(
+        &self,
+        config: &SpiDeviceConfig,
+        msg: &mut SpiMessage,
+    ) -> Result<(), SpiError> {
+        self.validate(config, msg)?;
+        msg.actual_length = 0;
+
+        let mut kept_cs = self.kept_cs.lock();
+        if let Some(kept_config) = kept_cs.take() {
+            if kept_config.chip_select != config.chip_select {
+                self.controller.set_cs(&kept_config, false);
+            }
+        }
+
+        self.controller.prepare_message(config)?;
+        self.controller.set_cs(config, true);
+
+        let nr_transfers = msg.transfers.len();
+        for (i, transfer) in msg.transfers.iter_mut().enumerate() {
+            if let Err(err) = self.controller.transfer_one(config, transfer) {
+                self.controller.set_cs(config, false);
+                return Err(err);
+            }
+            msg.actual_length += transfer.len;
+            delay(transfer.delay);
+
+            if !transfer.cs_change {
+                continue;
+            }
+            if i + 1 == nr_transfers {
+                *kept_cs = Some(*config);
+            } else {
+                self.controller.set_cs(config, false);
+                delay(CS_CHANGE_DELAY);
+                self.controller.set_cs(config, true);
+            }
+        }
+
+        if kept_cs.is_none() {
+            self.controller.set_cs(config, false);
+        }
+        Ok(())
+    }
+
+    fn validate(&self, config: &SpiDeviceConfig, msg: &mut SpiMessage) -> Result<(), SpiError> {
+        if msg.transfers.is_empty() {
+            return Err(SpiError::InvalidArgs);
+        }
+
+        for transfer in msg.transfers.iter_mut() {
+            let len = transfer.len;
+            let is_buf_invalid =
+                |buf: &Option<Vec<u8>>| buf.as_ref().is_some_and(|buf| buf.len() != len);
+            if is_buf_invalid(&transfer.tx_buf) || is_buf_invalid(&transfer.rx_buf) {
+                return Err(SpiError::InvalidArgs);
+            }
+
+            if transfer.speed_hz == 0 {
+                transfer.speed_hz = config.max_speed_hz;
+            }
+            transfer.speed_hz = transfer.speed_hz.min(self.controller.max_speed_hz());
+
+            if transfer.bits_per_word == 0 {
+                transfer.bits_per_word = config.bits_per_word;
+            }
+            if !is_bits_per_word_supported(self.controller.as_ref(), transfer.bits_per_word) {
+                return Err(SpiError::InvalidArgs);
+            }
+            if transfer.len % bytes_per_word(transfer.bits_per_word) != 0 {
+                return Err(SpiError::InvalidArgs);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn is_bits_per_word_supported(controller: &dyn SpiController, bits: u8) -> bool {
+    (1..=32).contains(&bits) && controller.bits_per_word_mask() & (1 << (bits - 1)) != 0
+}
+
+/// Returns the number of bytes in which a word is stored.
+fn bytes_per_word(bits: u8) -> usize {
+    match bits {
+        1..=8 => 1,
+        9..=16 => 2,
+        _ => 4,
+    }
+}
+
+/// Waits for at least the duration.
+fn delay(duration: Duration) {
+    if duration.is_zero() {
+        return;
+    }
+
+    let deadline = Jiffies::elapsed().as_duration() + duration;
+    while Jiffies::elapsed().as_duration() < deadline {
+        Task::yield_now();
+    }
+}
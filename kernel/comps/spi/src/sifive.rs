@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the SiFive SPI controller.
+//!
+//! Only the single data wire protocol and 8-bit words are supported. The FIFOs are polled, since
+//! the interrupts are not routed to the driver.
+//!
+//! Reference: SiFive FU540-C000 Manual, Chapter 19 (Serial Peripheral Interface).
+
+use alloc::{string::ToString, sync::Arc};
+use core::time::Duration;
+
+use log::warn;
+use ostd::{arch::device::SIFIVE_SPI_NODES, io::IoMem, mm::VmIoOnce, task::Task, timer::Jiffies};
+
+use crate::{
+    register_board_info, register_controller, SpiBoardInfo, SpiController, SpiDeviceConfig,
+    SpiError, SpiMode, SpiTransfer,
+};
+
+pub(super) fn init() {
+    let Some(nodes) = SIFIVE_SPI_NODES.get() else {
+        return;
+    };
+
+    for node in nodes {
+        let input_clock_hz = node.clock_hz.unwrap_or(DEFAULT_INPUT_CLOCK_HZ);
+        let Some(controller) = SifiveSpi::new(node.io_mem.clone(), input_clock_hz) else {
+            warn!("[SPI]: The SiFive SPI controller has no chip selects");
+            continue;
+        };
+        let bus_nr = register_controller(Arc::new(controller));
+
+        for device in node.devices.iter() {
+            let mut mode = SpiMode::MODE_0;
+            mode.set(SpiMode::CPHA, device.cpha);
+            mode.set(SpiMode::CPOL, device.cpol);
+            mode.set(SpiMode::CS_HIGH, device.cs_high);
+            mode.set(SpiMode::LSB_FIRST, device.lsb_first);
+
+            let info = SpiBoardInfo {
+                name: device.compatible.to_string(),
+                chip_select: device.chip_select as u16,
+                mode,
+                max_speed_hz: device.max_speed_hz.unwrap_or(0),
+            };
+            register_board_info(bus_nr, info);
+        }
+    }
+}
+
+/// The frequency of the input clock if it is unknown.
+///
+/// This is the frequency of the TileLink bus clock of FU540, which drives the controllers.
+const DEFAULT_INPUT_CLOCK_HZ: u64 = 500_000_000;
+
+/// The number of the entries in each FIFO.
+const FIFO_DEPTH: usize = 8;
+
+/// The maximum time to wait for the FIFOs.
+const FIFO_TIMEOUT: Duration = Duration::from_millis(100);
+
+// The offsets of the registers.
+const SCKDIV: usize = 0x00;
+const SCKMODE: usize = 0x04;
+const CSID: usize = 0x10;
+const CSDEF: usize = 0x14;
+const CSMODE: usize = 0x18;
+const DELAY0: usize = 0x28;
+const DELAY1: usize = 0x2c;
+const FMT: usize = 0x40;
+const TXDATA: usize = 0x48;
+const RXDATA: usize = 0x4c;
+const TXMARK: usize = 0x50;
+const RXMARK: usize = 0x54;
+const FCTRL: usize = 0x60;
+const IE: usize = 0x70;
+const IP: usize = 0x74;
+
+const SCKDIV_MASK: u32 = 0xfff;
+
+/// The chip select is deasserted after each frame, which deasserts it if there are no frames.
+const CSMODE_AUTO: u32 = 0;
+/// The chip select is kept asserted after the first frame.
+const CSMODE_HOLD: u32 = 2;
+
+const FMT_PROTO_SINGLE: u32 = 0;
+const FMT_ENDIAN_LSB: u32 = 1 << 2;
+/// The received frames are not pushed into the RX FIFO.
+const FMT_DIR_TX: u32 = 1 << 3;
+const FMT_LEN_SHIFT: u32 = 16;
+
+const TXDATA_FULL: u32 = 1 << 31;
+const RXDATA_EMPTY: u32 = 1 << 31;
+
+/// The TX FIFO has fewer entries than the TX watermark.
+const IP_TXWM: u32 = 1 << 0;
+/// The RX FIFO has more entries than the RX watermark.
+const IP_RXWM: u32 = 1 << 1;
+
+#[derive(Debug)]
+struct SifiveSpi {
+    io_mem: IoMem,
+    input_clock_hz: u64,
+    num_chip_selects: u16,
+}
+
+impl SifiveSpi {
+    /// Initializes the controller.
+    ///
+    /// This method fails if the controller has no chip selects.
+    fn new(io_mem: IoMem, input_clock_hz: u64) -> Option<Self> {
+        let mut spi = Self {
+            io_mem,
+            input_clock_hz,
+            num_chip_selects: 0,
+        };
+
+        // The interrupts are not used.
+        spi.write(IE, 0);
+        // The TX watermark is hit when the TX FIFO is empty, and the RX watermark is hit when
+        // the RX FIFO is not empty.
+        spi.write(TXMARK, 1);
+        spi.write(RXMARK, 0);
+        // Use the default delays between the chip select and the clock.
+        spi.write(DELAY0, 1 | (1 << 16));
+        spi.write(DELAY1, 1);
+        // Leave the memory-mapped flash mode set up by the firmware.
+        spi.write(FCTRL, 0);
+
+        // Only the bits of the existing chip selects are writable.
+        let cs_inactive = spi.read(CSDEF);
+        spi.write(CSDEF, u32::MAX);
+        let cs_bits = spi.read(CSDEF);
+        spi.write(CSDEF, cs_inactive);
+        if cs_bits == 0 {
+            return None;
+        }
+        spi.num_chip_selects = (u32::BITS - cs_bits.leading_zeros()) as u16;
+
+        spi.write(CSMODE, CSMODE_AUTO);
+        Some(spi)
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        // The offsets are within the registers described by the device tree.
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap()
+    }
+
+    fn wait_for(&self, pending: u32) -> Result<(), SpiError> {
+        let deadline = Jiffies::elapsed().as_duration() + FIFO_TIMEOUT;
+
+        while self.read(IP) & pending == 0 {
+            if Jiffies::elapsed().as_duration() >= deadline {
+                warn!("[SPI]: The SiFive SPI transfer timed out");
+                return Err(SpiError::Timeout);
+            }
+            Task::yield_now();
+        }
+
+        Ok(())
+    }
+}
+
+impl SpiController for SifiveSpi {
+    fn name(&self) -> &str {
+        "sifive-spi"
+    }
+
+    fn num_chip_selects(&self) -> u16 {
+        self.num_chip_selects
+    }
+
+    fn mode_bits(&self) -> SpiMode {
+        SpiMode::CPHA | SpiMode::CPOL | SpiMode::CS_HIGH | SpiMode::LSB_FIRST
+    }
+
+    fn bits_per_word_mask(&self) -> u32 {
+        1 << (8 - 1)
+    }
+
+    fn max_speed_hz(&self) -> u32 {
+        (self.input_clock_hz / 2).min(u32::MAX as u64) as u32
+    }
+
+    fn prepare_message(&self, config: &SpiDeviceConfig) -> Result<(), SpiError> {
+        let cs_bit = 1 << config.chip_select;
+        let cs_inactive = if config.mode.contains(SpiMode::CS_HIGH) {
+            self.read(CSDEF) & !cs_bit
+        } else {
+            self.read(CSDEF) | cs_bit
+        };
+        self.write(CSDEF, cs_inactive);
+
+        self.write(CSID, config.chip_select as u32);
+        self.write(
+            SCKMODE,
+            (config.mode & (SpiMode::CPHA | SpiMode::CPOL)).bits(),
+        );
+        Ok(())
+    }
+
+    fn set_cs(&self, _config: &SpiDeviceConfig, is_asserted: bool) {
+        // The polarity of the chip select has been set by `prepare_message`.
+        let csmode = if is_asserted {
+            CSMODE_HOLD
+        } else {
+            CSMODE_AUTO
+        };
+        self.write(CSMODE, csmode);
+    }
+
+    fn transfer_one(
+        &self,
+        config: &SpiDeviceConfig,
+        transfer: &mut SpiTransfer,
+    ) -> Result<(), SpiError> {
+        // The frequency of the clock is `input_clock_hz / (2 * (div + 1))`.
+        let div = (self.input_clock_hz / 2).div_ceil(transfer.speed_hz.max(1) as u64) - 1;
+        self.write(SCKDIV, (div as u32).min(SCKDIV_MASK));
+
+        let mut fmt = FMT_PROTO_SINGLE | ((transfer.bits_per_word as u32) << FMT_LEN_SHIFT);
+        if config.mode.contains(SpiMode::LSB_FIRST) {
+            fmt |= FMT_ENDIAN_LSB;
+        }
+        if transfer.rx_buf.is_none() {
+            fmt |= FMT_DIR_TX;
+        }
+        self.write(FMT, fmt);
+
+        let mut pos = 0;
+        while pos < transfer.len {
+            let nr_words = (transfer.len - pos).min(FIFO_DEPTH);
+
+            if transfer.rx_buf.is_some() {
+                self.write(RXMARK, nr_words as u32 - 1);
+            }
+
+            // The TX FIFO is empty, since the previous words have been sent.
+            for i in pos..pos + nr_words {
+                if self.read(TXDATA) & TXDATA_FULL != 0 {
+                    return Err(SpiError::Io);
+                }
+                let byte = transfer.tx_buf.as_ref().map_or(0, |tx_buf| tx_buf[i]);
+                self.write(TXDATA, byte as u32);
+            }
+
+            if let Some(rx_buf) = transfer.rx_buf.as_mut() {
+                self.wait_for(IP_RXWM)?;
+                for byte in rx_buf[pos..pos + nr_words].iter_mut() {
+                    let rxdata = self.read(RXDATA);
+                    if rxdata & RXDATA_EMPTY != 0 {
+                        return Err(SpiError::Io);
+                    }
+                    *byte = rxdata as u8;
+                }
+            } else {
+                self.wait_for(IP_TXWM)?;
+            }
+
+            pos += nr_words;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Organizes the devices in a `SysTree` in the same way as Linux.
+//!
+//! The device model populates the following directories under the root:
+//!  - `devices`: the hierarchy of all devices, in which a device is
+//!    placed under its parent (e.g., the PCI device that it sits on).
+//!    Devices without a physical parent are placed under
+//!    `devices/platform` or `devices/virtual`;
+//!  - `bus/<bus>/devices`: the symlinks to the devices on a bus;
+//!  - `class/<class>`: the symlinks to the devices of a class,
+//!    grouped by what they do rather than how they are connected;
+//!  - `kernel`: the runtime tunables of the kernel.
+//!
+//! Every device on a bus or of a class has a `subsystem` symlink
+//! that points back to the bus or class directory.
+//! Adding or removing a device generates a uevent (see [`crate::uevent`]).
+
+use alloc::{borrow::Cow, format, sync::Arc};
+
+use super::{
+    kobject::{KObject, KObjectBuilder, KSymlink},
+    node::{SysBranchNode, SysObj, SysSymlink},
+    tree::RootNode,
+    uevent::{send_uevent, uevent_seqnum, UeventAction},
+    Error, Result, SysStr,
+};
+
+/// The device model of a `SysTree`.
+#[derive(Debug)]
+pub struct DeviceModel {
+    devices: Arc<KObject>,
+    platform: Arc<KObject>,
+    virtual_: Arc<KObject>,
+    bus: Arc<KObject>,
+    class: Arc<KObject>,
+    kernel: Arc<KObject>,
+}
+
+impl DeviceModel {
+    /// Creates the device model and adds its top-level directories to the root.
+    pub(crate) fn new(root: &RootNode) -> Result<Self> {
+        let devices = KObjectBuilder::new(Cow::Borrowed("devices")).build(root)?;
+        let platform = KObjectBuilder::new(Cow::Borrowed("platform")).build(devices.as_ref())?;
+        devices.add_child(platform.clone())?;
+        let virtual_ = KObjectBuilder::new(Cow::Borrowed("virtual")).build(devices.as_ref())?;
+        devices.add_child(virtual_.clone())?;
+        let bus = KObjectBuilder::new(Cow::Borrowed("bus")).build(root)?;
+        let class = KObjectBuilder::new(Cow::Borrowed("class")).build(root)?;
+        let kernel = {
+            let mut builder = KObjectBuilder::new(Cow::Borrowed("kernel"));
+            builder.attr_with("uevent_seqnum", || format!("{}", uevent_seqnum()));
+            builder.build(root)?
+        };
+
+        for dir in [&devices, &bus, &class, &kernel] {
+            root.add_child(dir.clone())?;
+        }
+
+        Ok(Self {
+            devices,
+            platform,
+            virtual_,
+            bus,
+            class,
+            kernel,
+        })
+    }
+
+    /// Returns the `devices` directory.
+    pub fn devices(&self) -> &Arc<KObject> {
+        &self.devices
+    }
+
+    /// Returns the `devices/platform` directory,
+    /// which holds the devices that are not discoverable on a bus.
+    pub fn platform(&self) -> &Arc<KObject> {
+        &self.platform
+    }
+
+    /// Returns the `devices/virtual` directory,
+    /// which holds the devices that are not backed by hardware.
+    pub fn virtual_(&self) -> &Arc<KObject> {
+        &self.virtual_
+    }
+
+    /// Returns the `devices/virtual/<class>` directory,
+    /// which holds the virtual devices of a class.
+    pub fn virtual_class_dir(&self, class: &'static str) -> Result<Arc<KObject>> {
+        if let Some(dir) = self.virtual_.child_kobj(class) {
+            return Ok(dir);
+        }
+
+        let dir = KObjectBuilder::new(Cow::Borrowed(class)).build(self.virtual_.as_ref())?;
+        self.add_or_get(&self.virtual_, dir)
+    }
+
+    /// Returns the `kernel` directory,
+    /// into which the kernel subsystems can add their runtime tunables.
+    pub fn kernel(&self) -> &Arc<KObject> {
+        &self.kernel
+    }
+
+    /// Registers a bus, creating its `devices` and `drivers` directories.
+    ///
+    /// If the bus has been registered, the existing bus directory is returned.
+    pub fn register_bus(&self, name: &'static str) -> Result<Arc<KObject>> {
+        if let Some(bus) = self.bus.child_kobj(name) {
+            return Ok(bus);
+        }
+
+        let bus = KObjectBuilder::new(Cow::Borrowed(name)).build(self.bus.as_ref())?;
+        for dir_name in ["devices", "drivers"] {
+            let dir = KObjectBuilder::new(Cow::Borrowed(dir_name)).build(bus.as_ref())?;
+            bus.add_child(dir)?;
+        }
+        self.add_or_get(&self.bus, bus)
+    }
+
+    /// Registers a class.
+    ///
+    /// If the class has been registered, the existing class directory is returned.
+    pub fn register_class(&self, name: &'static str) -> Result<Arc<KObject>> {
+        if let Some(class) = self.class.child_kobj(name) {
+            return Ok(class);
+        }
+
+        let class = KObjectBuilder::new(Cow::Borrowed(name)).build(self.class.as_ref())?;
+        self.add_or_get(&self.class, class)
+    }
+
+    /// Adds a directory to `parent` unless a directory of the same name
+    /// has been added concurrently, in which case the latter is returned.
+    fn add_or_get(&self, parent: &KObject, dir: Arc<KObject>) -> Result<Arc<KObject>> {
+        match parent.add_child(dir.clone()) {
+            Ok(()) => Ok(dir),
+            Err(err) => parent.child_kobj(&dir.name()).ok_or(err),
+        }
+    }
+
+    /// Adds a device under `parent`, and links it to a bus and a class if given.
+    ///
+    /// An `add` uevent is generated once the device is added.
+    ///
+    /// The device must have been built with `parent` as its parent.
+    /// The bus and the class must have been registered.
+    pub fn add_device(
+        &self,
+        parent: &KObject,
+        device: Arc<KObject>,
+        bus: Option<&str>,
+        class: Option<&str>,
+    ) -> Result<()> {
+        let bus = bus
+            .map(|name| {
+                self.bus
+                    .child_kobj(name)
+                    .ok_or(Error::InternalError("the bus is not registered"))
+            })
+            .transpose()?;
+        let class = class
+            .map(|name| {
+                self.class
+                    .child_kobj(name)
+                    .ok_or(Error::InternalError("the class is not registered"))
+            })
+            .transpose()?;
+
+        // A device belongs to its bus if it has one, otherwise to its class.
+        if let Some(subsystem) = bus.as_ref().or(class.as_ref()) {
+            device.add_child(new_link("subsystem", &device, subsystem.as_ref()))?;
+            device.set_subsystem(subsystem.name());
+        }
+        parent.add_child(device.clone())?;
+
+        if let Some(bus) = bus {
+            let bus_devices = bus
+                .child_kobj("devices")
+                .ok_or(Error::InternalError("the bus has no devices directory"))?;
+            bus_devices.add_child(new_link(device.name(), &bus_devices, device.as_ref()))?;
+        }
+        if let Some(class) = class {
+            class.add_child(new_link(device.name(), &class, device.as_ref()))?;
+        }
+
+        send_uevent(UeventAction::Add, &device);
+        Ok(())
+    }
+
+    /// Removes the device with the given name from `parent`,
+    /// together with the symlinks to it from its bus and class.
+    ///
+    /// A `remove` uevent is generated once the device is removed.
+    pub fn remove_device(&self, parent: &KObject, name: &str) -> Result<Arc<KObject>> {
+        let device = parent
+            .child_kobj(name)
+            .ok_or(Error::InternalError("the device does not exist"))?;
+
+        let bus_dirs = self.bus.children().into_iter().filter_map(|bus| {
+            let bus = KObject::downcast(bus.as_ref())?;
+            bus.child_kobj("devices")
+        });
+        let class_dirs = self
+            .class
+            .children()
+            .into_iter()
+            .filter_map(|class| KObject::downcast(class.as_ref()));
+        for dir in bus_dirs.chain(class_dirs) {
+            remove_link(&dir, &device);
+        }
+        parent.remove_child(name);
+
+        send_uevent(UeventAction::Remove, &device);
+        Ok(device)
+    }
+}
+
+/// Removes the symlink in `dir` that points to `device`, if any.
+fn remove_link(dir: &KObject, device: &KObject) {
+    let name = device.name();
+    let Some(link) = dir.child(&name).and_then(|child| child.arc_as_symlink()) else {
+        return;
+    };
+    let expected = new_link(name.clone(), dir, device);
+    if link.target_path() == expected.target_path() {
+        dir.remove_child(&name);
+    }
+}
+
+/// Creates a symlink in `dir` that points to `target`.
+fn new_link(name: impl Into<SysStr>, dir: &KObject, target: &dyn SysObj) -> Arc<KSymlink> {
+    KSymlink::new(name.into(), &dir.path(), target)
+}
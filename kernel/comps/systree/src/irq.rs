@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Exposes the IRQ lines that are bound to MSI-X vectors, and lets userspace control which CPUs
+//! handle them.
+//!
+//! Each IRQ line gets a directory at `kernel/irq/<N>`, which contains the following attributes:
+//!  - `chip_name` and `type`, as in Linux;
+//!  - `smp_affinity` and `smp_affinity_list`, which show the CPUs that are allowed to handle the
+//!    interrupts as a hexadecimal bitmap and as a list of ranges, respectively. Writing to them
+//!    changes the affinity;
+//!  - `effective_affinity` and `effective_affinity_list`, which show the CPU that the interrupts
+//!    are actually delivered to;
+//!  - `affinity_hint`, which shows the CPUs that are suggested by the driver.
+//!
+//! Linux exposes the affinity attributes in `/proc/irq/<N>` instead.
+//!
+//! In addition, the `msi_irqs` directory of a PCI device has an attribute named after each IRQ
+//! line of the device, whose value is `msix`.
+
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use ostd::{
+    bus::pci::{
+        capability::msix::{self, IrqAffinity},
+        PciDeviceLocation,
+    },
+    cpu::{num_cpus, CpuId, CpuSet},
+};
+
+use super::{
+    device::DeviceModel,
+    device_model,
+    kobject::KObjectBuilder,
+    pci::{pci_device, slot_name},
+    Error, Result,
+};
+
+/// The name of the directory that holds the IRQ lines.
+const IRQ_DIR_NAME: &str = "irq";
+
+/// The name of the directory of a PCI device that holds its MSI-X IRQ lines.
+const MSI_IRQS_DIR_NAME: &str = "msi_irqs";
+
+pub(crate) fn init(model: &DeviceModel) -> Result<()> {
+    let irq_dir =
+        KObjectBuilder::new(Cow::Borrowed(IRQ_DIR_NAME)).build(model.kernel().as_ref())?;
+    model.kernel().add_child(irq_dir)?;
+
+    // The drivers that have been initialized may have bound some IRQ lines.
+    msix::register_bind_listener(on_bind);
+    for irq_num in msix::bound_irqs() {
+        on_bind(irq_num);
+    }
+    Ok(())
+}
+
+fn on_bind(irq_num: u8) {
+    // The errors are ignored, since they can only be caused by a directory that is being
+    // replaced concurrently, and a driver cannot fail because of the view of its IRQ lines.
+    let Some(affinity) = msix::irq_affinity(irq_num) else {
+        return;
+    };
+    let _ = add_irq_dir(irq_num, &affinity.location);
+    let _ = update_msi_irqs_dir(&affinity.location);
+}
+
+/// Adds the `kernel/irq/<N>` directory, which replaces the old one if the IRQ line is rebound.
+fn add_irq_dir(irq_num: u8, location: &PciDeviceLocation) -> Result<()> {
+    let irq_dir = device_model()
+        .kernel()
+        .child_kobj(IRQ_DIR_NAME)
+        .ok_or(Error::InternalError("the IRQ directory does not exist"))?;
+
+    let mut builder = KObjectBuilder::new(Cow::Owned(irq_num.to_string()));
+    builder
+        .attr("chip_name", format!("PCI-MSIX-{}", slot_name(location)))
+        .attr("type", "edge".to_string())
+        .attr_rw(
+            "smp_affinity",
+            move || show_affinity(irq_num, |affinity| format_cpu_mask(&affinity.affinity)),
+            move |value| store_affinity(irq_num, parse_cpu_mask(value)),
+        )
+        .attr_rw(
+            "smp_affinity_list",
+            move || show_affinity(irq_num, |affinity| format_cpu_list(&affinity.affinity)),
+            move |value| store_affinity(irq_num, parse_cpu_list(value)),
+        )
+        .attr_with("effective_affinity", move || {
+            show_affinity(irq_num, |affinity| {
+                format_cpu_mask(&CpuSet::from(affinity.effective_cpu))
+            })
+        })
+        .attr_with("effective_affinity_list", move || {
+            show_affinity(irq_num, |affinity| {
+                format_cpu_list(&CpuSet::from(affinity.effective_cpu))
+            })
+        })
+        .attr_with("affinity_hint", move || {
+            show_affinity(irq_num, |affinity| {
+                // Like Linux, an empty bitmap is shown if there is no hint.
+                let hint = affinity
+                    .affinity_hint
+                    .clone()
+                    .unwrap_or_else(CpuSet::new_empty);
+                format_cpu_mask(&hint)
+            })
+        });
+    let kobj = builder.build(irq_dir.as_ref())?;
+
+    irq_dir.remove_child(&kobj.name());
+    irq_dir.add_child(kobj)
+}
+
+/// Rebuilds the `msi_irqs` directory of a PCI device with all its IRQ lines.
+fn update_msi_irqs_dir(location: &PciDeviceLocation) -> Result<()> {
+    let Some(device) = pci_device(location) else {
+        return Ok(());
+    };
+
+    let mut builder = KObjectBuilder::new(Cow::Borrowed(MSI_IRQS_DIR_NAME));
+    for irq_num in msix::bound_irqs() {
+        if msix::irq_affinity(irq_num).is_some_and(|affinity| affinity.location == *location) {
+            builder.attr(irq_num.to_string(), "msix".to_string());
+        }
+    }
+    let kobj = builder.build(device.as_ref())?;
+
+    device.remove_child(MSI_IRQS_DIR_NAME);
+    device.add_child(kobj)
+}
+
+fn show_affinity(irq_num: u8, show: impl Fn(&IrqAffinity) -> String) -> String {
+    // The IRQ line may have been rebound to another vector, which is still fine to show.
+    msix::irq_affinity(irq_num)
+        .map(|affinity| show(&affinity))
+        .unwrap_or_default()
+}
+
+fn store_affinity(irq_num: u8, cpus: Option<CpuSet>) -> Result<()> {
+    let cpus = cpus.ok_or(Error::AttributeError)?;
+    msix::set_irq_affinity(irq_num, &cpus).map_err(|_| Error::AttributeError)
+}
+
+/// Formats a CPU set as a hexadecimal bitmap, e.g., `f` for CPUs 0-3.
+///
+/// Like Linux, the bitmap has a digit for every four CPUs in the system,
+/// and the digits are grouped by 32 bits with commas.
+pub(crate) fn format_cpu_mask(cpus: &CpuSet) -> String {
+    let nr_cpus = num_cpus();
+    let mut words = vec![0u32; nr_cpus.div_ceil(32)];
+    for cpu in cpus.iter() {
+        words[cpu.as_usize() / 32] |= 1 << (cpu.as_usize() % 32);
+    }
+
+    let last = words.len() - 1;
+    let mut mask = format!(
+        "{:0width$x}",
+        words[last],
+        width = (nr_cpus - last * 32).div_ceil(4)
+    );
+    for word in words[..last].iter().rev() {
+        mask.push_str(&format!(",{:08x}", word));
+    }
+    mask
+}
+
+/// Formats a CPU set as a list of ranges, e.g., `0-3,5`.
+pub(crate) fn format_cpu_list(cpus: &CpuSet) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for cpu in cpus.iter().map(CpuId::as_usize) {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+
+    let ranges: Vec<String> = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect();
+    ranges.join(",")
+}
+
+/// Parses a CPU set from a hexadecimal bitmap.
+///
+/// Returns `None` if the bitmap is malformed or contains CPUs that do not exist.
+pub(crate) fn parse_cpu_mask(value: &str) -> Option<CpuSet> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut cpus = CpuSet::new_empty();
+    let digits = value.chars().filter(|ch| *ch != ',');
+    for (i, ch) in digits.rev().enumerate() {
+        let digit = ch.to_digit(16)?;
+        for bit in 0..4 {
+            if digit & (1 << bit) != 0 {
+                cpus.add(CpuId::try_from(i * 4 + bit).ok()?);
+            }
+        }
+    }
+    Some(cpus)
+}
+
+/// Parses a CPU set from a list of ranges.
+///
+/// Returns `None` if the list is malformed or contains CPUs that do not exist.
+pub(crate) fn parse_cpu_list(value: &str) -> Option<CpuSet> {
+    let mut cpus = CpuSet::new_empty();
+    for range in value.trim().split(',') {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+            None => {
+                let cpu = range.parse().ok()?;
+                (cpu, cpu)
+            }
+        };
+        if start > end {
+            return None;
+        }
+        for cpu in start..=end {
+            cpus.add(CpuId::try_from(cpu).ok()?);
+        }
+    }
+    Some(cpus)
+}
@@ -0,0 +1,405 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Defines `KObject`, a general-purpose branching node,
+//! and `KSymlink`, a general-purpose symlink node.
+//!
+//! Most nodes in a `SysTree` are directories that
+//! hold a few textual attributes and some child nodes.
+//! Instead of implementing the node traits for every kind of such nodes,
+//! the controllers (e.g., buses and drivers) can describe their nodes
+//! with `KObjectBuilder` and let `KObject` do the bookkeeping,
+//! much like the `kobject` of Linux.
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::{any::Any, fmt::Debug};
+
+use ostd::mm::{FallibleVmRead, FallibleVmWrite, VmReader, VmWriter};
+use spin::Once;
+
+use super::{
+    attr::{SysAttrFlags, SysAttrSet, SysAttrSetBuilder},
+    node::{SysBranchNode, SysNode, SysNodeId, SysNodeType, SysObj, SysSymlink, MAX_ATTR_SIZE},
+    uevent::{send_uevent, UeventAction},
+    utils::{SymlinkNodeFields, SysBranchNodeFields},
+    Error, Result, SysStr,
+};
+
+/// The function that shows the value of an attribute.
+pub type AttrShowFn = dyn Fn() -> String + Send + Sync;
+
+/// The function that stores a new value to an attribute.
+///
+/// The new value has its trailing newline trimmed.
+pub type AttrStoreFn = dyn Fn(&str) -> Result<()> + Send + Sync;
+
+/// The name of the attribute that reports the uevent environment of an object.
+const UEVENT_ATTR: &str = "uevent";
+
+enum AttrOps {
+    Custom {
+        show: Box<AttrShowFn>,
+        store: Option<Box<AttrStoreFn>>,
+    },
+    /// The `uevent` attribute.
+    ///
+    /// Reading the attribute shows the uevent environment of the object,
+    /// while writing an action to it generates a uevent of the action.
+    Uevent,
+}
+
+impl Debug for AttrOps {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Custom { store, .. } => f
+                .debug_struct("Custom")
+                .field("can_store", &store.is_some())
+                .finish(),
+            Self::Uevent => f.write_str("Uevent"),
+        }
+    }
+}
+
+/// A branching node whose attributes are backed by functions.
+#[derive(Debug)]
+pub struct KObject {
+    fields: SysBranchNodeFields<dyn SysObj>,
+    attr_ops: BTreeMap<SysStr, AttrOps>,
+    uevent_env: Vec<(&'static str, String)>,
+    /// The name of the bus or the class that the object belongs to.
+    subsystem: Once<SysStr>,
+    path: String,
+    self_ref: Weak<Self>,
+}
+
+impl KObject {
+    /// Downcasts a node to a `KObject`.
+    pub fn downcast(obj: &dyn SysObj) -> Option<Arc<KObject>> {
+        obj.as_any().downcast_ref::<KObject>()?.self_ref.upgrade()
+    }
+
+    /// Adds a child node.
+    ///
+    /// This method fails if there is already a child with the same name.
+    pub fn add_child(&self, child: Arc<dyn SysObj>) -> Result<()> {
+        self.fields.add_child(child)
+    }
+
+    /// Removes the child node with the given name.
+    pub fn remove_child(&self, name: &str) -> Option<Arc<dyn SysObj>> {
+        self.fields.remove_child(name)
+    }
+
+    /// Returns the child with the given name if the child is a `KObject`.
+    pub fn child_kobj(&self, name: &str) -> Option<Arc<KObject>> {
+        let children = self.fields.children.read();
+        Self::downcast(children.get(name)?.as_ref())
+    }
+
+    /// Returns the object-specific environment of the uevents, e.g., `DEVNAME`.
+    pub fn uevent_env(&self) -> &[(&'static str, String)] {
+        &self.uevent_env
+    }
+
+    /// Returns the name of the bus or the class that the object belongs to.
+    pub fn subsystem(&self) -> Option<SysStr> {
+        self.subsystem.get().cloned()
+    }
+
+    pub(crate) fn set_subsystem(&self, name: SysStr) {
+        self.subsystem.call_once(|| name);
+    }
+
+    fn show_uevent(&self) -> String {
+        self.uevent_env
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect()
+    }
+}
+
+impl SysObj for KObject {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn arc_as_node(&self) -> Option<Arc<dyn SysNode>> {
+        self.self_ref
+            .upgrade()
+            .map(|arc_self| arc_self as Arc<dyn SysNode>)
+    }
+
+    fn arc_as_branch(&self) -> Option<Arc<dyn SysBranchNode>> {
+        self.self_ref
+            .upgrade()
+            .map(|arc_self| arc_self as Arc<dyn SysBranchNode>)
+    }
+
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Branch
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name().to_string().into()
+    }
+
+    fn path(&self) -> SysStr {
+        self.path.clone().into()
+    }
+}
+
+impl SysNode for KObject {
+    fn node_attrs(&self) -> &SysAttrSet {
+        self.fields.attr_set()
+    }
+
+    fn read_attr(&self, name: &str, writer: &mut VmWriter) -> Result<usize> {
+        let mut value = match self.attr_ops.get(name).ok_or(Error::AttributeError)? {
+            AttrOps::Custom { show, .. } => show(),
+            AttrOps::Uevent => self.show_uevent(),
+        };
+        // Like Linux, every non-empty value ends with a newline.
+        if !value.is_empty() && !value.ends_with('\n') {
+            value.push('\n');
+        }
+        writer
+            .write_fallible(&mut value.as_bytes().into())
+            .map_err(|_| Error::AttributeError)
+    }
+
+    fn write_attr(&self, name: &str, reader: &mut VmReader) -> Result<usize> {
+        let ops = self.attr_ops.get(name).ok_or(Error::AttributeError)?;
+
+        let mut buffer = vec![0u8; reader.remain().min(MAX_ATTR_SIZE)];
+        let mut writer = VmWriter::from(buffer.as_mut_slice());
+        let read_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::AttributeError)?;
+        let value = core::str::from_utf8(&buffer[..read_len]).map_err(|_| Error::AttributeError)?;
+
+        let value = value.trim_end_matches('\n');
+        match ops {
+            AttrOps::Custom {
+                store: Some(store), ..
+            } => store(value)?,
+            AttrOps::Custom { store: None, .. } => return Err(Error::PermissionDenied),
+            AttrOps::Uevent => {
+                // Like Linux, the arguments after the action are ignored.
+                let action = value.split_whitespace().next().unwrap_or("");
+                let action = UeventAction::try_from(action).map_err(|_| Error::AttributeError)?;
+                send_uevent(action, self);
+            }
+        }
+        Ok(read_len)
+    }
+}
+
+impl SysBranchNode for KObject {
+    fn visit_child_with(&self, name: &str, f: &mut dyn FnMut(Option<&dyn SysNode>)) {
+        let children = self.fields.children.read();
+        match children.get(name).and_then(|child| child.arc_as_node()) {
+            Some(node) => f(Some(node.as_ref())),
+            None => f(None),
+        }
+    }
+
+    fn visit_children_with(&self, min_id: u64, f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>) {
+        let children = self.fields.children.read();
+        for child in children
+            .values()
+            .filter(|child| child.id().as_u64() >= min_id)
+        {
+            if f(child).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<Arc<dyn SysObj>> {
+        self.fields.children.read().get(name).cloned()
+    }
+
+    fn count_children(&self) -> usize {
+        self.fields.children.read().len()
+    }
+}
+
+/// A builder of `KObject`.
+pub struct KObjectBuilder {
+    name: SysStr,
+    attr_set_builder: SysAttrSetBuilder,
+    attr_ops: BTreeMap<SysStr, AttrOps>,
+    uevent_env: Vec<(&'static str, String)>,
+}
+
+impl KObjectBuilder {
+    /// Creates a builder of a `KObject` with the given name.
+    pub fn new(name: SysStr) -> Self {
+        Self {
+            name,
+            attr_set_builder: SysAttrSetBuilder::new(),
+            attr_ops: BTreeMap::new(),
+            uevent_env: Vec::new(),
+        }
+    }
+
+    /// Adds a read-only attribute with a constant value.
+    pub fn attr(&mut self, name: impl Into<SysStr>, value: String) -> &mut Self {
+        self.attr_with(name, move || value.clone())
+    }
+
+    /// Adds a read-only attribute whose value is shown by a function.
+    pub fn attr_with<S>(&mut self, name: impl Into<SysStr>, show: S) -> &mut Self
+    where
+        S: Fn() -> String + Send + Sync + 'static,
+    {
+        self.add_attr(
+            name,
+            AttrOps::Custom {
+                show: Box::new(show),
+                store: None,
+            },
+        )
+    }
+
+    /// Adds a writable attribute, which can be used as a runtime tunable.
+    pub fn attr_rw<S, T>(&mut self, name: impl Into<SysStr>, show: S, store: T) -> &mut Self
+    where
+        S: Fn() -> String + Send + Sync + 'static,
+        T: Fn(&str) -> Result<()> + Send + Sync + 'static,
+    {
+        self.add_attr(
+            name,
+            AttrOps::Custom {
+                show: Box::new(show),
+                store: Some(Box::new(store)),
+            },
+        )
+    }
+
+    /// Adds the `uevent` attribute with the object-specific environment of the uevents.
+    ///
+    /// Userspace may write an action (e.g., `add`) to the attribute
+    /// to request the uevent to be generated again.
+    pub fn uevent(&mut self, env: Vec<(&'static str, String)>) -> &mut Self {
+        self.uevent_env = env;
+        self.add_attr(UEVENT_ATTR, AttrOps::Uevent)
+    }
+
+    fn add_attr(&mut self, name: impl Into<SysStr>, ops: AttrOps) -> &mut Self {
+        let name = name.into();
+        let flags = match ops {
+            AttrOps::Custom { store: None, .. } => SysAttrFlags::CAN_READ,
+            _ => SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE,
+        };
+        self.attr_set_builder.add(name.clone(), flags);
+        self.attr_ops.entry(name).or_insert(ops);
+        self
+    }
+
+    /// Builds the `KObject`, which is going to be a child of `parent`.
+    ///
+    /// The caller is responsible for adding the object to its parent.
+    pub fn build(self, parent: &dyn SysObj) -> Result<Arc<KObject>> {
+        let attr_set = self.attr_set_builder.build()?;
+        let path = join_path(&parent.path(), &self.name);
+        let fields = SysBranchNodeFields::new(self.name, attr_set);
+
+        Ok(Arc::new_cyclic(|weak_self| KObject {
+            fields,
+            attr_ops: self.attr_ops,
+            uevent_env: self.uevent_env,
+            subsystem: Once::new(),
+            path,
+            self_ref: weak_self.clone(),
+        }))
+    }
+}
+
+/// A symlink node that points to another node with a relative path.
+#[derive(Debug)]
+pub struct KSymlink {
+    fields: SymlinkNodeFields,
+    self_ref: Weak<Self>,
+}
+
+impl KSymlink {
+    /// Creates a symlink in the directory at `dir_path` that points to `target`.
+    pub fn new(name: SysStr, dir_path: &str, target: &dyn SysObj) -> Arc<Self> {
+        let target_path = relative_path(dir_path, &target.path());
+        Arc::new_cyclic(|weak_self| KSymlink {
+            fields: SymlinkNodeFields::new(name, target_path),
+            self_ref: weak_self.clone(),
+        })
+    }
+}
+
+impl SysObj for KSymlink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn arc_as_symlink(&self) -> Option<Arc<dyn SysSymlink>> {
+        self.self_ref
+            .upgrade()
+            .map(|arc_self| arc_self as Arc<dyn SysSymlink>)
+    }
+
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Symlink
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name().to_string().into()
+    }
+}
+
+impl SysSymlink for KSymlink {
+    fn target_path(&self) -> &str {
+        self.fields.target_path()
+    }
+}
+
+/// Joins the path of a directory with the name of an entry in it.
+fn join_path(dir_path: &str, name: &str) -> String {
+    if dir_path.ends_with('/') {
+        format!("{}{}", dir_path, name)
+    } else {
+        format!("{}/{}", dir_path, name)
+    }
+}
+
+/// Computes the path of `target` relative to the directory at `dir_path`.
+///
+/// Both paths must be absolute.
+pub(crate) fn relative_path(dir_path: &str, target: &str) -> String {
+    let dir_components: Vec<&str> = dir_path.split('/').filter(|s| !s.is_empty()).collect();
+    let target_components: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+    let common_len = dir_components
+        .iter()
+        .zip(target_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut components = vec![".."; dir_components.len() - common_len];
+    components.extend_from_slice(&target_components[common_len..]);
+    if components.is_empty() {
+        return ".".to_string();
+    }
+    components.join("/")
+}
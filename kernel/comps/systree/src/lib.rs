@@ -23,10 +23,15 @@
 extern crate alloc;
 
 mod attr;
+mod device;
+mod irq;
+mod kobject;
 mod node;
+mod pci;
 #[cfg(ktest)]
 mod test;
 mod tree;
+mod uevent;
 mod utils;
 
 use alloc::{borrow::Cow, sync::Arc};
@@ -36,16 +41,27 @@ use spin::Once;
 
 pub use self::{
     attr::{SysAttr, SysAttrFlags, SysAttrSet, SysAttrSetBuilder},
-    node::{SysBranchNode, SysNode, SysNodeId, SysNodeType, SysObj, SysSymlink},
+    device::DeviceModel,
+    kobject::{AttrShowFn, AttrStoreFn, KObject, KObjectBuilder, KSymlink},
+    node::{SysBranchNode, SysNode, SysNodeId, SysNodeType, SysObj, SysSymlink, MAX_ATTR_SIZE},
+    pci::{pci_device, PCI_BUS_NAME},
     tree::SysTree,
+    uevent::{register_uevent_listener, uevent_seqnum, Uevent, UeventAction, UeventListener},
     utils::{SymlinkNodeFields, SysBranchNodeFields, SysNormalNodeFields, SysObjFields},
 };
 
 static SINGLETON: Once<Arc<SysTree>> = Once::new();
 
+static DEVICE_MODEL: Once<DeviceModel> = Once::new();
+
 #[init_component]
 fn init() -> core::result::Result<(), ComponentInitError> {
     SINGLETON.call_once(|| Arc::new(SysTree::new()));
+    let device_model =
+        DeviceModel::new(singleton().root()).map_err(|_| ComponentInitError::Unknown)?;
+    DEVICE_MODEL.call_once(|| device_model);
+    pci::init(self::device_model()).map_err(|_| ComponentInitError::Unknown)?;
+    irq::init(self::device_model()).map_err(|_| ComponentInitError::Unknown)?;
     Ok(())
 }
 
@@ -59,6 +75,13 @@ pub fn singleton() -> &'static Arc<SysTree> {
     SINGLETON.get().expect("SysTree not initialized")
 }
 
+/// Returns a reference to the device model of the global SysTree. Panics if not initialized.
+pub fn device_model() -> &'static DeviceModel {
+    DEVICE_MODEL
+        .get()
+        .expect("the device model is not initialized")
+}
+
 /// An owned string or a static reference to string.
 pub type SysStr = Cow<'static, str>;
 
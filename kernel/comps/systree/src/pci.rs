@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Adds the devices found on the PCI bus to the device model.
+
+use alloc::{borrow::Cow, format, string::String, sync::Arc, vec};
+
+use ostd::bus::pci::{PciDeviceId, PciDeviceLocation, PCI_BUS};
+
+use super::{
+    device::DeviceModel,
+    device_model,
+    kobject::{KObject, KObjectBuilder},
+    Result,
+};
+
+/// The name of the PCI bus in the device model.
+pub const PCI_BUS_NAME: &str = "pci";
+
+/// The name of the directory of the PCI host bridge.
+///
+/// PCI-to-PCI bridges are not modeled yet,
+/// so all the PCI devices are placed directly under the host bridge.
+const HOST_BRIDGE_NAME: &str = "pci0000:00";
+
+pub(crate) fn init(model: &DeviceModel) -> Result<()> {
+    let found_devices = PCI_BUS.lock().found_devices().to_vec();
+    if found_devices.is_empty() {
+        return Ok(());
+    }
+
+    model.register_bus(PCI_BUS_NAME)?;
+    let host_bridge =
+        KObjectBuilder::new(Cow::Borrowed(HOST_BRIDGE_NAME)).build(model.devices().as_ref())?;
+    model.devices().add_child(host_bridge.clone())?;
+
+    for (location, id) in found_devices {
+        let device = new_device(&host_bridge, &location, &id)?;
+        model.add_device(&host_bridge, device, Some(PCI_BUS_NAME), None)?;
+    }
+    Ok(())
+}
+
+/// Returns the directory of the PCI device at the given location.
+pub fn pci_device(location: &PciDeviceLocation) -> Option<Arc<KObject>> {
+    device_model()
+        .devices()
+        .child_kobj(HOST_BRIDGE_NAME)?
+        .child_kobj(&slot_name(location))
+}
+
+fn new_device(
+    host_bridge: &KObject,
+    location: &PciDeviceLocation,
+    id: &PciDeviceId,
+) -> Result<Arc<KObject>> {
+    let class = ((id.class as u32) << 16) | ((id.subclass as u32) << 8) | id.prog_if as u32;
+    let modalias = format!(
+        "pci:v{:08X}d{:08X}sv{:08X}sd{:08X}bc{:02X}sc{:02X}i{:02X}",
+        id.vendor_id,
+        id.device_id,
+        id.subsystem_vendor_id,
+        id.subsystem_id,
+        id.class,
+        id.subclass,
+        id.prog_if
+    );
+
+    let mut builder = KObjectBuilder::new(Cow::Owned(slot_name(location)));
+    builder
+        .attr("vendor", format!("{:#06x}", id.vendor_id))
+        .attr("device", format!("{:#06x}", id.device_id))
+        .attr(
+            "subsystem_vendor",
+            format!("{:#06x}", id.subsystem_vendor_id),
+        )
+        .attr("subsystem_device", format!("{:#06x}", id.subsystem_id))
+        .attr("class", format!("{:#08x}", class))
+        .attr("revision", format!("{:#04x}", id.revision_id))
+        .attr("modalias", modalias.clone())
+        .uevent(vec![
+            ("PCI_CLASS", format!("{:X}", class)),
+            (
+                "PCI_ID",
+                format!("{:04X}:{:04X}", id.vendor_id, id.device_id),
+            ),
+            (
+                "PCI_SUBSYS_ID",
+                format!("{:04X}:{:04X}", id.subsystem_vendor_id, id.subsystem_id),
+            ),
+            ("PCI_SLOT_NAME", slot_name(location)),
+            ("MODALIAS", modalias),
+        ]);
+    builder.build(host_bridge)
+}
+
+/// Returns the name of a PCI device, e.g., `0000:00:03.0`.
+pub(crate) fn slot_name(location: &PciDeviceLocation) -> String {
+    format!(
+        "0000:{:02x}:{:02x}.{:x}",
+        location.bus, location.device, location.function
+    )
+}
@@ -4,18 +4,27 @@ use alloc::{
     borrow::Cow,
     string::{String, ToString},
     sync::{Arc, Weak},
+    vec,
     vec::Vec,
 };
-use core::{any::Any, fmt::Debug};
+use core::{
+    any::Any,
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use ostd::{
+    cpu::num_cpus,
     mm::{FallibleVmRead, FallibleVmWrite, VmReader, VmWriter},
     prelude::ktest,
+    sync::Mutex,
 };
 
 use super::{
-    Error, Result, SysAttrFlags, SysAttrSet, SysAttrSetBuilder, SysBranchNode, SysBranchNodeFields,
-    SysNode, SysNodeId, SysNodeType, SysObj, SysStr, SysSymlink, SysTree,
+    irq::{format_cpu_list, format_cpu_mask, parse_cpu_list, parse_cpu_mask},
+    register_uevent_listener, uevent_seqnum, DeviceModel, Error, KObject, KObjectBuilder, Result,
+    SysAttrFlags, SysAttrSet, SysAttrSetBuilder, SysBranchNode, SysBranchNodeFields, SysNode,
+    SysNodeId, SysNodeType, SysObj, SysStr, SysSymlink, SysTree, Uevent, UeventAction,
 };
 
 #[derive(Debug)]
@@ -328,3 +337,166 @@ fn error_handling() {
     let child = device.child("nonexistent");
     assert!(child.is_none());
 }
+
+#[ktest]
+fn kobject_attributes() {
+    let tree = SysTree::new();
+    let tunable = Arc::new(AtomicUsize::new(1));
+
+    let mut builder = KObjectBuilder::new(Cow::Borrowed("kobj"));
+    builder
+        .attr("model", "MyDevice".to_string())
+        .attr_rw(
+            "tunable",
+            {
+                let tunable = tunable.clone();
+                move || tunable.load(Ordering::Relaxed).to_string()
+            },
+            {
+                let tunable = tunable.clone();
+                move |value| {
+                    let value = value.parse().map_err(|_| Error::AttributeError)?;
+                    tunable.store(value, Ordering::Relaxed);
+                    Ok(())
+                }
+            },
+        )
+        .uevent(vec![("MAJOR", "1".to_string()), ("MINOR", "3".to_string())]);
+    let kobj = builder.build(tree.root().as_ref()).unwrap();
+    assert_eq!(kobj.path(), "/kobj");
+    assert_eq!(kobj.type_(), SysNodeType::Branch);
+
+    assert_eq!(kobj.show_attr("model").unwrap(), "MyDevice\n");
+    assert!(matches!(
+        kobj.store_attr("model", "NewModel"),
+        Err(Error::PermissionDenied)
+    ));
+
+    assert_eq!(kobj.show_attr("tunable").unwrap(), "1\n");
+    assert_eq!(kobj.store_attr("tunable", "42\n").unwrap(), 3);
+    assert_eq!(tunable.load(Ordering::Relaxed), 42);
+    assert_eq!(kobj.show_attr("tunable").unwrap(), "42\n");
+    assert!(kobj.store_attr("tunable", "invalid").is_err());
+
+    assert_eq!(kobj.show_attr("uevent").unwrap(), "MAJOR=1\nMINOR=3\n");
+    assert!(kobj.store_attr("uevent", "add").is_ok());
+    assert!(kobj.store_attr("uevent", "invalid").is_err());
+}
+
+#[ktest]
+fn device_model() {
+    let tree = SysTree::new();
+    let model = DeviceModel::new(tree.root()).unwrap();
+    for name in ["devices", "bus", "class", "kernel"] {
+        assert!(tree.root().child(name).is_some());
+    }
+
+    let bus = model.register_bus("test_bus").unwrap();
+    assert!(Arc::ptr_eq(&bus, &model.register_bus("test_bus").unwrap()));
+    model.register_class("test_class").unwrap();
+
+    let platform = model.platform();
+    let device = KObjectBuilder::new(Cow::Borrowed("dev0"))
+        .build(platform.as_ref())
+        .unwrap();
+    model
+        .add_device(platform, device, Some("test_bus"), Some("test_class"))
+        .unwrap();
+
+    let device = platform.child_kobj("dev0").unwrap();
+    assert_eq!(device.path(), "/devices/platform/dev0");
+    let link_target = |dir: &KObject, name: &str| {
+        let link = dir.child(name).unwrap().arc_as_symlink().unwrap();
+        link.target_path().to_string()
+    };
+    assert_eq!(link_target(&device, "subsystem"), "../../../bus/test_bus");
+    let bus_devices = bus.child_kobj("devices").unwrap();
+    assert_eq!(
+        link_target(&bus_devices, "dev0"),
+        "../../../devices/platform/dev0"
+    );
+    let class = model.register_class("test_class").unwrap();
+    assert_eq!(link_target(&class, "dev0"), "../../devices/platform/dev0");
+
+    // Devices cannot be added twice or to unregistered buses.
+    let device = KObjectBuilder::new(Cow::Borrowed("dev0"))
+        .build(platform.as_ref())
+        .unwrap();
+    assert!(model.add_device(platform, device, None, None).is_err());
+    let device = KObjectBuilder::new(Cow::Borrowed("dev1"))
+        .build(platform.as_ref())
+        .unwrap();
+    assert!(model
+        .add_device(platform, device, Some("no_bus"), None)
+        .is_err());
+    assert!(platform.child("dev1").is_none());
+}
+
+#[ktest]
+fn uevents() {
+    static EVENTS: Mutex<Vec<(UeventAction, String, Option<String>)>> = Mutex::new(Vec::new());
+    fn record(event: &Uevent) {
+        if event.devpath().starts_with("/devices/virtual/uevent_test") {
+            EVENTS.lock().push((
+                event.action(),
+                event.devpath().to_string(),
+                event.var("DEVNAME").map(ToString::to_string),
+            ));
+        }
+    }
+    register_uevent_listener(&record);
+
+    let tree = SysTree::new();
+    let model = DeviceModel::new(tree.root()).unwrap();
+    model.register_class("uevent_test").unwrap();
+    let parent = model.virtual_class_dir("uevent_test").unwrap();
+    assert!(Arc::ptr_eq(
+        &parent,
+        &model.virtual_class_dir("uevent_test").unwrap()
+    ));
+
+    let seqnum = uevent_seqnum();
+    let mut builder = KObjectBuilder::new(Cow::Borrowed("dev0"));
+    builder.uevent(vec![("DEVNAME", "dev0".to_string())]);
+    let device = builder.build(parent.as_ref()).unwrap();
+    model
+        .add_device(&parent, device.clone(), None, Some("uevent_test"))
+        .unwrap();
+    assert_eq!(device.subsystem().as_deref(), Some("uevent_test"));
+    assert!(device.store_attr("uevent", "change").is_ok());
+    model.remove_device(&parent, "dev0").unwrap();
+    assert!(uevent_seqnum() >= seqnum + 3);
+
+    // The device and the symlink to it are gone.
+    assert!(parent.child("dev0").is_none());
+    let class = model.register_class("uevent_test").unwrap();
+    assert!(class.child("dev0").is_none());
+    assert!(model.remove_device(&parent, "dev0").is_err());
+
+    let devpath = "/devices/virtual/uevent_test/dev0".to_string();
+    let devname = Some("dev0".to_string());
+    assert_eq!(
+        *EVENTS.lock(),
+        vec![
+            (UeventAction::Add, devpath.clone(), devname.clone()),
+            (UeventAction::Change, devpath.clone(), devname.clone()),
+            (UeventAction::Remove, devpath, devname),
+        ]
+    );
+}
+
+#[ktest]
+fn cpu_masks_and_lists() {
+    let cpus = parse_cpu_list("0").unwrap();
+    assert_eq!(format_cpu_list(&cpus), "0");
+    assert!(format_cpu_mask(&cpus).ends_with('1'));
+    assert_eq!(format_cpu_list(&parse_cpu_mask("1").unwrap()), "0");
+    assert_eq!(format_cpu_list(&parse_cpu_mask("0,00000001").unwrap()), "0");
+
+    // Malformed values and nonexistent CPUs are rejected.
+    for value in ["", "x", "1-0", "0-"] {
+        assert!(parse_cpu_list(value).is_none());
+    }
+    assert!(parse_cpu_mask("g").is_none());
+    assert!(parse_cpu_list(&num_cpus().to_string()).is_none());
+}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel object events (uevents).
+//!
+//! A uevent is generated when a device is added to or removed from the device model,
+//! or when userspace writes an action to the `uevent` attribute of a `KObject`
+//! (e.g., to replay the `add` events of the devices found at boot time).
+//! The events are delivered to the registered listeners,
+//! such as the `NETLINK_KOBJECT_UEVENT` sockets that udev-like daemons listen to.
+//!
+//! Each event carries a sequence number that is increased by one for each event,
+//! which is also reported by `/sys/kernel/uevent_seqnum`.
+
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ostd::sync::RwLock;
+
+use super::{kobject::KObject, node::SysObj, SysStr};
+
+/// The action of a uevent.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/linux/kobject.h#L53>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UeventAction {
+    Add,
+    Remove,
+    Change,
+    Move,
+    Online,
+    Offline,
+    Bind,
+    Unbind,
+}
+
+impl UeventAction {
+    const ALL: [Self; 8] = [
+        Self::Add,
+        Self::Remove,
+        Self::Change,
+        Self::Move,
+        Self::Online,
+        Self::Offline,
+        Self::Bind,
+        Self::Unbind,
+    ];
+
+    /// Returns the name of the action, e.g., `add`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Change => "change",
+            Self::Move => "move",
+            Self::Online => "online",
+            Self::Offline => "offline",
+            Self::Bind => "bind",
+            Self::Unbind => "unbind",
+        }
+    }
+}
+
+impl TryFrom<&str> for UeventAction {
+    type Error = ();
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == name)
+            .ok_or(())
+    }
+}
+
+/// A uevent.
+#[derive(Debug, Clone)]
+pub struct Uevent {
+    action: UeventAction,
+    devpath: SysStr,
+    subsystem: Option<SysStr>,
+    env: Vec<(&'static str, String)>,
+    seqnum: u64,
+}
+
+impl Uevent {
+    /// Returns the action.
+    pub fn action(&self) -> UeventAction {
+        self.action
+    }
+
+    /// Returns the path of the object in the `SysTree`, e.g., `/devices/virtual/mem/null`.
+    pub fn devpath(&self) -> &str {
+        &self.devpath
+    }
+
+    /// Returns the bus or the class that the object belongs to.
+    pub fn subsystem(&self) -> Option<&str> {
+        self.subsystem.as_deref()
+    }
+
+    /// Returns the value of a variable in the object-specific environment, e.g., `DEVNAME`.
+    pub fn var(&self, key: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the sequence number.
+    pub fn seqnum(&self) -> u64 {
+        self.seqnum
+    }
+
+    /// Visits all the variables of the environment in the same order as Linux.
+    ///
+    /// The variables are `ACTION`, `DEVPATH`, `SUBSYSTEM`, the object-specific ones,
+    /// and finally `SEQNUM`.
+    pub fn visit_env(&self, mut f: impl FnMut(&str, &str)) {
+        f("ACTION", self.action.as_str());
+        f("DEVPATH", &self.devpath);
+        if let Some(subsystem) = self.subsystem.as_deref() {
+            f("SUBSYSTEM", subsystem);
+        }
+        for (key, value) in self.env.iter() {
+            f(key, value);
+        }
+        f("SEQNUM", &format!("{}", self.seqnum));
+    }
+}
+
+/// A function that listens to the uevents.
+pub type UeventListener = dyn Fn(&Uevent) + Send + Sync;
+
+static LISTENERS: RwLock<Vec<&'static UeventListener>> = RwLock::new(Vec::new());
+
+/// The sequence number of the last uevent.
+static SEQNUM: AtomicU64 = AtomicU64::new(0);
+
+/// Registers a listener, which is called for every uevent from now on.
+pub fn register_uevent_listener(listener: &'static UeventListener) {
+    LISTENERS.write().push(listener);
+}
+
+/// Returns the sequence number of the last uevent.
+pub fn uevent_seqnum() -> u64 {
+    SEQNUM.load(Ordering::Relaxed)
+}
+
+/// Generates a uevent of a `KObject` and delivers it to the listeners.
+pub(crate) fn send_uevent(action: UeventAction, kobj: &KObject) {
+    let event = Uevent {
+        action,
+        devpath: kobj.path(),
+        subsystem: kobj.subsystem(),
+        env: kobj.uevent_env().to_vec(),
+        seqnum: SEQNUM.fetch_add(1, Ordering::Relaxed) + 1,
+    };
+
+    for listener in LISTENERS.read().iter() {
+        listener(&event);
+    }
+}
@@ -11,7 +11,12 @@
 //! or used standalone for time tracking and elapsed time measurements.
 
 use alloc::sync::Arc;
-use core::{cmp::max, ops::Add, time::Duration};
+use core::{
+    cmp::max,
+    ops::Add,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use aster_util::coeff::Coeff;
 use ostd::sync::{LocalIrqDisabled, RwLock};
@@ -43,42 +48,70 @@ use crate::NANOS_PER_SECOND;
 /// // here we set the max_delay_secs = 10
 /// let max_delay_secs = 10;
 /// // create a clocksource named counter_clock
-/// let counter_clock = ClockSource::new(counter.freq, max_delay_secs, Arc::new(read_counter));
+/// let counter_clock = ClockSource::new(
+///     "counter",
+///     100,
+///     counter.freq,
+///     u64::MAX,
+///     max_delay_secs,
+///     Arc::new(read_counter),
+/// );
 /// // read an instant.
 /// let instant = counter_clock.read_instant();
 /// ```
 ///
 /// If using this `ClockSource`, you must ensure its internal instant will be updated
 /// at least once within a time interval of not more than `max_delay_secs.
+///
+/// # Stability
+/// A `ClockSource` is stable until it is marked as unstable, e.g., when it runs at a
+/// different rate from other clocksources or when it goes backwards. If the counter is
+/// found to be behind the last record, which happens if the counters are not synchronized
+/// among CPUs, the last recorded instant is returned so that time never goes backwards.
 pub struct ClockSource {
+    name: &'static str,
+    rating: u32,
     read_cycles: Arc<dyn Fn() -> u64 + Sync + Send>,
     base: ClockSourceBase,
     coeff: Coeff,
     /// A record to an `Instant` and the corresponding cycles of this `ClockSource`.
     last_record: RwLock<(Instant, u64), LocalIrqDisabled>,
+    is_stable: AtomicBool,
 }
 
 impl ClockSource {
     /// Creates a new `ClockSource` instance.
     /// Require basic information of based time counter, including the function for reading cycles,
-    /// the frequency and the maximum delay seconds to update this `ClockSource`.
+    /// the frequency, the mask of the valid bits of the counter and the maximum delay seconds to
+    /// update this `ClockSource`.
     /// The `ClockSource` also calculates a reliable `Coeff` based on the counter's frequency and
     /// the maximum delay seconds. This `Coeff` is used to convert the number of cycles into
     /// the duration of time that has passed for those cycles.
+    ///
+    /// The `name` identifies the `ClockSource`, and the `rating` tells how good it is. The
+    /// stable `ClockSource` with the highest rating is used to measure time.
     pub fn new(
+        name: &'static str,
+        rating: u32,
         freq: u64,
+        mask: u64,
         max_delay_secs: u64,
         read_cycles: Arc<dyn Fn() -> u64 + Sync + Send>,
     ) -> Self {
-        let base = ClockSourceBase::new(freq, max_delay_secs);
+        let base = ClockSourceBase::new(freq, mask, max_delay_secs);
         // Too big `max_delay_secs` will lead to a low resolution Coeff.
         debug_assert!(max_delay_secs < 600);
+        // The counter must not wrap around (by more than a half) between two updates.
+        debug_assert!(base.max_delay_secs * freq <= mask / 2);
         let coeff = Coeff::new(NANOS_PER_SECOND as u64, freq, max_delay_secs * freq);
         Self {
+            name,
+            rating,
             read_cycles,
             base,
             coeff,
             last_record: RwLock::new((Instant::zero(), 0)),
+            is_stable: AtomicBool::new(true),
         }
     }
 
@@ -96,15 +129,29 @@ impl ClockSource {
             (self.read_cycles(), last_instant, last_cycles)
         };
 
-        let delta_nanos = {
-            let delta_cycles = instant_cycles - last_cycles;
-            self.cycles_to_nanos_lossy(delta_cycles)
-        };
-        let duration = Duration::from_nanos(delta_nanos);
+        let delta_cycles = self.delta_cycles(last_cycles, instant_cycles);
+        if delta_cycles > self.base.mask / 2 {
+            // The counter goes backwards. This should never happen for a stable `ClockSource`
+            // with a 64-bit counter. But a narrower counter may have wrapped around if the last
+            // record has not been updated in time.
+            if self.base.mask == u64::MAX {
+                self.mark_unstable();
+            }
+            return (last_instant, last_cycles);
+        }
+
+        let duration = Duration::from_nanos(self.cycles_to_nanos_lossy(delta_cycles));
         (last_instant + duration, instant_cycles)
     }
 
-    fn cycles_to_nanos_lossy(&self, cycles: u64) -> u64 {
+    /// Returns the number of cycles that have passed from `from` to `to`.
+    ///
+    /// The counter can wrap around at most once in between.
+    pub(crate) fn delta_cycles(&self, from: u64, to: u64) -> u64 {
+        to.wrapping_sub(from) & self.base.mask
+    }
+
+    pub(crate) fn cycles_to_nanos_lossy(&self, cycles: u64) -> u64 {
         let max_cycles = self.base.max_delay_secs * self.base.freq;
         if cycles <= max_cycles {
             self.coeff * cycles
@@ -120,7 +167,7 @@ impl ClockSource {
     }
 
     /// Uses an input instant and cycles to update the `last_record` in the `ClockSource`.
-    fn update_last_record(&self, record: (Instant, u64)) {
+    pub(crate) fn update_last_record(&self, record: (Instant, u64)) {
         *self.last_record.write() = record;
     }
 
@@ -129,6 +176,26 @@ impl ClockSource {
         (self.read_cycles)()
     }
 
+    /// Returns the name of the `ClockSource`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the rating of the `ClockSource`.
+    pub fn rating(&self) -> u32 {
+        self.rating
+    }
+
+    /// Returns whether the `ClockSource` is stable.
+    pub fn is_stable(&self) -> bool {
+        self.is_stable.load(Ordering::Relaxed)
+    }
+
+    /// Marks the `ClockSource` as unstable, so it will no longer be used to measure time.
+    pub(crate) fn mark_unstable(&self) {
+        self.is_stable.store(false, Ordering::Relaxed);
+    }
+
     /// Returns the last instant and last cycles recorded in the `ClockSource`.
     pub fn last_record(&self) -> (Instant, u64) {
         return *self.last_record.read();
@@ -149,6 +216,11 @@ impl ClockSource {
         self.base.freq
     }
 
+    /// Returns the mask of the valid bits of the counter used in the `ClockSource`.
+    pub fn mask(&self) -> u64 {
+        self.base.mask
+    }
+
     /// Calibrates the recorded `Instant` to zero, and record the instant cycles.
     pub(crate) fn calibrate(&self, instant_cycles: u64) {
         self.update_last_record((Instant::zero(), instant_cycles));
@@ -227,14 +299,16 @@ impl Add<Duration> for Instant {
 #[derive(Debug, Copy, Clone)]
 struct ClockSourceBase {
     freq: u64,
+    mask: u64,
     max_delay_secs: u64,
 }
 
 impl ClockSourceBase {
-    fn new(freq: u64, max_delay_secs: u64) -> Self {
+    fn new(freq: u64, mask: u64, max_delay_secs: u64) -> Self {
         let max_delay_secs = max(2, max_delay_secs);
         ClockSourceBase {
             freq,
+            mask,
             max_delay_secs,
         }
     }
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module provides an instance of `ClockSource` based on the HPET main counter.
+use alloc::sync::Arc;
+
+use ostd::arch::timer::hpet;
+
+use crate::{clocksource::ClockSource, registry};
+
+const MAX_DELAY_SECS: u64 = 100;
+
+const RATING: u32 = 250;
+
+/// Registers the HPET clocksource if there is an HPET.
+pub(super) fn init() {
+    let Some(freq) = hpet::counter_freq() else {
+        return;
+    };
+    let mask = hpet::counter_mask();
+
+    // A 32-bit counter wraps around in less than a minute at high frequencies.
+    let max_delay_secs = MAX_DELAY_SECS.min(mask / 2 / freq);
+
+    registry::register(Arc::new(ClockSource::new(
+        "hpet",
+        RATING,
+        freq,
+        mask,
+        max_delay_secs,
+        Arc::new(hpet::read_counter),
+    )));
+}
@@ -7,30 +7,51 @@
 
 extern crate alloc;
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::time::Duration;
 
-use clocksource::ClockSource;
-pub use clocksource::Instant;
+pub use clocksource::{ClockSource, Instant};
 use component::{init_component, ComponentInitError};
 use ostd::sync::Mutex;
 use rtc::Driver;
 use spin::Once;
 
 mod clocksource;
+#[cfg(target_arch = "x86_64")]
+mod hpet;
+#[cfg(target_arch = "x86_64")]
+mod pm_timer;
+mod registry;
 mod rtc;
 mod tsc;
+mod watchdog;
 
 pub const NANOS_PER_SECOND: u32 = 1_000_000_000;
 pub static VDSO_DATA_HIGH_RES_UPDATE_FN: Once<Arc<dyn Fn(Instant, u64) + Sync + Send>> =
     Once::new();
+/// The function to update the VDSO data after the current clocksource is switched.
+pub static VDSO_DATA_CLOCKSOURCE_SWITCH_FN: Once<Arc<dyn Fn(&ClockSource) + Sync + Send>> =
+    Once::new();
 static RTC_DRIVER: Once<Arc<dyn Driver + Send + Sync>> = Once::new();
 
 #[init_component]
 fn time_init() -> Result<(), ComponentInitError> {
-    let rtc = rtc::init_rtc_driver().ok_or(ComponentInitError::Unknown)?;
-    RTC_DRIVER.call_once(|| rtc);
+    if let Some(rtc) = rtc::init_rtc_driver() {
+        RTC_DRIVER.call_once(|| rtc);
+    } else {
+        log::warn!("rtc: no RTC is found, the real time starts at the Unix epoch");
+    }
+
     tsc::init();
+    #[cfg(target_arch = "x86_64")]
+    {
+        hpet::init();
+        pm_timer::init();
+    }
+    registry::init();
+
+    // Calibrate the system time based on the RTC time.
+    START_TIME.call_once(|| read_rtc().unwrap_or(SystemTime::UNIX_EPOCH));
     Ok(())
 }
 
@@ -46,35 +67,41 @@ pub struct SystemTime {
 }
 
 impl SystemTime {
-    pub(crate) const fn zero() -> Self {
-        Self {
-            year: 0,
-            month: 0,
-            day: 0,
-            hour: 0,
-            minute: 0,
-            second: 0,
-            nanos: 0,
-        }
-    }
+    /// The Unix epoch, which is 1970-01-01 00:00:00.
+    pub const UNIX_EPOCH: Self = Self {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        nanos: 0,
+    };
 }
 
-pub(crate) static READ_TIME: Mutex<SystemTime> = Mutex::new(SystemTime::zero());
+/// Serializes the accesses to the RTC, since a read or a write takes several register accesses.
+static RTC_LOCK: Mutex<()> = Mutex::new(());
 pub(crate) static START_TIME: Once<SystemTime> = Once::new();
 
-/// get real time
-pub fn get_real_time() -> SystemTime {
-    read()
+/// Returns whether there is an RTC.
+pub fn has_rtc() -> bool {
+    RTC_DRIVER.get().is_some()
 }
 
-pub fn read() -> SystemTime {
-    update_time();
-    *READ_TIME.lock()
+/// Reads the time of the RTC, which is in UTC.
+pub fn read_rtc() -> Result<SystemTime, RtcError> {
+    let driver = RTC_DRIVER.get().ok_or(RtcError::NotFound)?;
+    let _guard = RTC_LOCK.lock();
+    Ok(driver.read_rtc())
 }
 
-fn update_time() {
-    let mut lock = READ_TIME.lock();
-    *lock = RTC_DRIVER.get().unwrap().read_rtc();
+/// Writes the time to the RTC, which is in UTC.
+///
+/// The time should be a valid date and time. The system time is not affected.
+pub fn write_rtc(time: SystemTime) -> Result<(), RtcError> {
+    let driver = RTC_DRIVER.get().ok_or(RtcError::NotFound)?;
+    let _guard = RTC_LOCK.lock();
+    driver.write_rtc(time)
 }
 
 /// Return the `START_TIME`, which is the actual time when doing calibrate.
@@ -82,13 +109,43 @@ pub fn read_start_time() -> SystemTime {
     *START_TIME.get().unwrap()
 }
 
-/// Return the monotonic time from the tsc clocksource.
+/// Return the monotonic time from the current clocksource.
 pub fn read_monotonic_time() -> Duration {
-    let instant = tsc::read_instant();
+    let instant = registry::read_instant();
     Duration::new(instant.secs(), instant.nanos())
 }
 
-/// Return the tsc clocksource.
+/// Return the current clocksource.
 pub fn default_clocksource() -> Arc<ClockSource> {
-    tsc::CLOCK.get().unwrap().clone()
+    registry::current()
+}
+
+/// Returns the names of all the available clocksources.
+pub fn available_clocksources() -> Vec<&'static str> {
+    registry::names()
+}
+
+/// Switches the current clocksource to the one with `name`.
+///
+/// The monotonic time keeps going forward from the instant of the switch.
+pub fn select_clocksource(name: &str) -> Result<(), ClockSourceError> {
+    registry::select(name)
+}
+
+/// The errors of selecting a clocksource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSourceError {
+    /// There is no clocksource with the given name.
+    NotFound,
+    /// The clocksource has been marked as unstable.
+    Unstable,
+}
+
+/// The errors of accessing the RTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcError {
+    /// There is no RTC.
+    NotFound,
+    /// The time cannot be represented by the RTC.
+    InvalidTime,
 }
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module provides an instance of `ClockSource` based on the ACPI PM timer.
+use alloc::sync::Arc;
+
+use ostd::arch::timer::pm_timer::{self, PM_TIMER_FREQ};
+
+use crate::{clocksource::ClockSource, registry};
+
+/// The maximum delay seconds, which is limited by the 24-bit counter that wraps
+/// around every 4.69 seconds.
+const MAX_DELAY_SECS: u64 = 2;
+
+const RATING: u32 = 200;
+
+/// Registers the PM timer clocksource if there is a PM timer.
+pub(super) fn init() {
+    let Some(mask) = pm_timer::counter_mask() else {
+        return;
+    };
+
+    registry::register(Arc::new(ClockSource::new(
+        "acpi_pm",
+        RATING,
+        PM_TIMER_FREQ,
+        mask,
+        MAX_DELAY_SECS,
+        Arc::new(pm_timer::read_counter),
+    )));
+}
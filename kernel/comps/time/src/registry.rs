@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of clocksources.
+//!
+//! All the available clocksources are registered here with their ratings. The stable
+//! clocksource with the highest rating is selected as the current clocksource, which
+//! is used to measure time. If the current clocksource turns out to be unstable (see
+//! [`crate::watchdog`]), the best remaining one is selected at the next timer interrupt.
+//!
+//! Switching the current clocksource preserves the monotonicity of time: the new
+//! clocksource continues from the instant at which the old one stops.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    sync::{LocalIrqDisabled, RwLock, SpinLock},
+    timer,
+};
+use spin::Once;
+
+use crate::{
+    clocksource::{ClockSource, Instant},
+    watchdog, ClockSourceError, VDSO_DATA_CLOCKSOURCE_SWITCH_FN, VDSO_DATA_HIGH_RES_UPDATE_FN,
+};
+
+/// All the registered clocksources.
+static CLOCKSOURCES: SpinLock<Vec<Arc<ClockSource>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// The current clocksource.
+static CURRENT: Once<RwLock<Arc<ClockSource>, LocalIrqDisabled>> = Once::new();
+
+/// Registers a clocksource.
+///
+/// The clocksource will not be used unless it is the best one during [`init`] or it is
+/// selected later.
+pub(crate) fn register(clock: Arc<ClockSource>) {
+    log::info!(
+        "clocksource: registered '{}' with rating {}, frequency {} Hz",
+        clock.name(),
+        clock.rating(),
+        clock.freq()
+    );
+    CLOCKSOURCES.lock().push(clock);
+}
+
+/// Selects the best clocksource as the current clocksource and starts timekeeping.
+///
+/// The instant of the current clocksource starts from zero.
+pub(crate) fn init() {
+    let clock = best_stable(|_| true).expect("no clocksource is available");
+    log::info!("clocksource: using '{}'", clock.name());
+
+    clock.calibrate(clock.read_cycles());
+    CURRENT.call_once(|| RwLock::new(clock));
+
+    init_timer();
+}
+
+/// Returns the current clocksource.
+pub(crate) fn current() -> Arc<ClockSource> {
+    CURRENT.get().unwrap().read().clone()
+}
+
+/// Reads an `Instant` of the current clocksource.
+pub(crate) fn read_instant() -> Instant {
+    CURRENT.get().unwrap().read().read_instant()
+}
+
+/// Returns the names of all the registered clocksources.
+pub(crate) fn names() -> Vec<&'static str> {
+    CLOCKSOURCES
+        .lock()
+        .iter()
+        .map(|clock| clock.name())
+        .collect()
+}
+
+/// Returns the stable clocksource with the highest rating among the ones that
+/// satisfy `filter`.
+pub(crate) fn best_stable(filter: impl Fn(&ClockSource) -> bool) -> Option<Arc<ClockSource>> {
+    best_stable_of(&CLOCKSOURCES.lock(), filter)
+}
+
+fn best_stable_of(
+    clocks: &[Arc<ClockSource>],
+    filter: impl Fn(&ClockSource) -> bool,
+) -> Option<Arc<ClockSource>> {
+    clocks
+        .iter()
+        .filter(|clock| clock.is_stable() && filter(clock))
+        .max_by_key(|clock| clock.rating())
+        .cloned()
+}
+
+/// Selects the clocksource with `name` as the current clocksource.
+pub(crate) fn select(name: &str) -> Result<(), ClockSourceError> {
+    let clock = CLOCKSOURCES
+        .lock()
+        .iter()
+        .find(|clock| clock.name() == name)
+        .cloned()
+        .ok_or(ClockSourceError::NotFound)?;
+    if !clock.is_stable() {
+        return Err(ClockSourceError::Unstable);
+    }
+
+    switch_to(clock);
+    Ok(())
+}
+
+/// Switches the current clocksource to `new`.
+fn switch_to(new: Arc<ClockSource>) {
+    let mut current = CURRENT.get().unwrap().write();
+    if Arc::ptr_eq(&current, &new) {
+        return;
+    }
+
+    // No one can read the time until the lock is released, so the new clocksource
+    // continues exactly from the last instant of the old one.
+    hand_over(&current, &new);
+    let old = core::mem::replace(&mut *current, new.clone());
+    drop(current);
+
+    log::warn!(
+        "clocksource: switched from '{}' to '{}'",
+        old.name(),
+        new.name()
+    );
+
+    if let Some(switch_fn) = VDSO_DATA_CLOCKSOURCE_SWITCH_FN.get() {
+        switch_fn(&new);
+    }
+}
+
+/// Makes `new` continue from the current instant of `old`.
+fn hand_over(old: &ClockSource, new: &ClockSource) {
+    let instant = old.read_instant();
+    new.update_last_record((instant, new.read_cycles()));
+}
+
+/// Updates the last record of the current clocksource.
+fn update_current() {
+    let current = CURRENT.get().unwrap().read();
+    current.update();
+    let (last_instant, last_cycles) = current.last_record();
+    drop(current);
+
+    // Update vdso data.
+    if let Some(update_fn) = VDSO_DATA_HIGH_RES_UPDATE_FN.get() {
+        update_fn(last_instant, last_cycles);
+    }
+}
+
+/// The number of timer interrupts since the timer is initialized.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// The value of `TICKS` when the current clocksource is last updated.
+static LAST_UPDATE_TICKS: AtomicU64 = AtomicU64::new(0);
+
+fn init_timer() {
+    let update = move || {
+        let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+        let current = current();
+
+        if ticks % watchdog::INTERVAL_TICKS == 0 {
+            watchdog::check(&current);
+        }
+
+        if !current.is_stable() {
+            if let Some(fallback) = best_stable(|_| true) {
+                switch_to(fallback);
+                LAST_UPDATE_TICKS.store(ticks, Ordering::Relaxed);
+                update_current();
+                return;
+            }
+        }
+
+        // The `max_delay_secs` should be set as `clock.max_delay_secs() >> 1` or something much smaller than `max_delay_secs`.
+        // This is because the initialization of this timer occurs during system startup,
+        // and the system will also undergo other initialization processes, during which time interrupts are disabled.
+        // This results in the actual trigger time of the timer being delayed by about 5 seconds compared to the set time.
+        // If without KVM, the delayed time will be larger.
+        // TODO: This is a temporary solution, and should be modified in the future.
+        let delay_counts = TIMER_FREQ * (current.max_delay_secs() >> 1);
+        if ticks - LAST_UPDATE_TICKS.load(Ordering::Relaxed) >= delay_counts {
+            LAST_UPDATE_TICKS.store(ticks, Ordering::Relaxed);
+            update_current();
+        }
+    };
+
+    // TODO: re-organize the code structure and use the `Timer` to achieve the updating.
+    timer::register_callback(update);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+    /// A clocksource whose counter only advances when told to.
+    struct MockClock {
+        clock: Arc<ClockSource>,
+        counter: Arc<AtomicU64>,
+    }
+
+    impl MockClock {
+        fn new(name: &'static str, rating: u32, freq: u64) -> Self {
+            let counter = Arc::new(AtomicU64::new(0));
+            let counter_cloned = counter.clone();
+            let clock = ClockSource::new(
+                name,
+                rating,
+                freq,
+                u64::MAX,
+                10,
+                Arc::new(move || counter_cloned.load(Ordering::Relaxed)),
+            );
+            Self {
+                clock: Arc::new(clock),
+                counter,
+            }
+        }
+
+        fn set_cycles(&self, cycles: u64) {
+            self.counter.store(cycles, Ordering::Relaxed);
+        }
+
+        fn advance_secs(&self, secs: u64) {
+            self.counter
+                .fetch_add(secs * self.clock.freq(), Ordering::Relaxed);
+        }
+
+        fn nanos(&self) -> u64 {
+            let instant = self.clock.read_instant();
+            instant.secs() * NANOS_PER_SEC + instant.nanos() as u64
+        }
+    }
+
+    fn name_of(clock: Option<Arc<ClockSource>>) -> Option<&'static str> {
+        clock.map(|clock| clock.name())
+    }
+
+    #[ktest]
+    fn select_by_rating() {
+        let low = MockClock::new("low", 100, NANOS_PER_SEC);
+        let high = MockClock::new("high", 300, NANOS_PER_SEC);
+        let mid = MockClock::new("mid", 200, NANOS_PER_SEC);
+        let clocks = [low.clock.clone(), high.clock.clone(), mid.clock.clone()];
+
+        assert_eq!(name_of(best_stable_of(&clocks, |_| true)), Some("high"));
+        assert_eq!(
+            name_of(best_stable_of(&clocks, |clock| clock.name() != "high")),
+            Some("mid")
+        );
+
+        // Unstable clocksources are never selected.
+        high.clock.mark_unstable();
+        assert_eq!(name_of(best_stable_of(&clocks, |_| true)), Some("mid"));
+        mid.clock.mark_unstable();
+        low.clock.mark_unstable();
+        assert_eq!(name_of(best_stable_of(&clocks, |_| true)), None);
+    }
+
+    #[ktest]
+    fn monotonic_across_switch() {
+        let old = MockClock::new("old", 300, NANOS_PER_SEC);
+        let new = MockClock::new("new", 200, 1_000_000);
+        old.clock.calibrate(old.clock.read_cycles());
+        old.advance_secs(5);
+        // The counters of different clocksources are unrelated.
+        new.set_cycles(123_456_789);
+
+        hand_over(&old.clock, &new.clock);
+        assert_eq!(new.nanos(), 5 * NANOS_PER_SEC);
+
+        // The new clocksource measures the time from then on.
+        old.advance_secs(100);
+        new.advance_secs(1);
+        assert_eq!(new.nanos(), 6 * NANOS_PER_SEC);
+
+        // The time never goes backwards, even if the new counter does.
+        new.clock.update();
+        new.set_cycles(0);
+        assert_eq!(new.nanos(), 6 * NANOS_PER_SEC);
+        assert!(!new.clock.is_stable());
+    }
+}
@@ -4,7 +4,7 @@ use core::sync::atomic::{AtomicU8, Ordering::Relaxed};
 
 use ostd::arch::device::cmos::{century_register, CMOS_ADDRESS, CMOS_DATA};
 
-use crate::SystemTime;
+use crate::{RtcError, SystemTime};
 use super::Driver;
 
 static CENTURY_REGISTER: AtomicU8 = AtomicU8::new(0);
@@ -14,6 +14,11 @@ fn get_cmos(reg: u8) -> u8 {
     CMOS_DATA.read()
 }
 
+fn set_cmos(reg: u8, value: u8) {
+    CMOS_ADDRESS.write(reg);
+    CMOS_DATA.write(value);
+}
+
 fn is_updating() -> bool {
     CMOS_ADDRESS.write(0x0A);
     CMOS_DATA.read() & 0x80 != 0
@@ -79,12 +84,45 @@ impl CmosData {
         }
     }
 
+    /// Converts binary values to BCD.
+    fn convert_binary_to_bcd(&mut self, register_b: u8) {
+        fn to_bcd(value: u8) -> u8 {
+            ((value / 10) << 4) | (value % 10)
+        }
+
+        if register_b & 0x04 == 0 {
+            self.second = to_bcd(self.second);
+            self.minute = to_bcd(self.minute);
+            self.hour = to_bcd(self.hour & 0x7F) | (self.hour & 0x80);
+            self.day = to_bcd(self.day);
+            self.month = to_bcd(self.month);
+            self.year = to_bcd(self.year as u8) as u16;
+            self.century = to_bcd(self.century);
+        }
+    }
+
     /// Converts 12 hour clock to 24 hour clock.
     fn convert_12_hour_to_24_hour(&mut self, register_b: u8) {
         // bit1 in register_b is not set if 12 hour format is enable
         // if highest bit in hour is set, then it is pm
-        if ((register_b & 0x02) == 0) && ((self.hour & 0x80) != 0) {
-            self.hour = ((self.hour & 0x7F) + 12) % 24;
+        if (register_b & 0x02) == 0 {
+            let is_pm = self.hour & 0x80 != 0;
+            // 12 AM is midnight and 12 PM is noon.
+            self.hour = (self.hour & 0x7F) % 12;
+            if is_pm {
+                self.hour += 12;
+            }
+        }
+    }
+
+    /// Converts 24 hour clock to 12 hour clock.
+    fn convert_24_hour_to_12_hour(&mut self, register_b: u8) {
+        if (register_b & 0x02) == 0 {
+            let pm_bit = if self.hour >= 12 { 0x80 } else { 0 };
+            self.hour = match self.hour % 12 {
+                0 => 12,
+                hour => hour,
+            } | pm_bit;
         }
     }
 
@@ -93,6 +131,12 @@ impl CmosData {
         self.year += self.century as u16 * 100;
     }
 
+    /// Splits real year (2010, 2020 etc.) into century and raw year (10, 20 etc.).
+    fn split_year(&mut self) {
+        self.century = (self.year / 100) as u8;
+        self.year %= 100;
+    }
+
     pub fn read_rtc(century_register: u8) -> Self {
         let mut now = Self::from_rtc_raw(century_register);
         while let new = Self::from_rtc_raw(century_register) && now != new {
@@ -107,6 +151,44 @@ impl CmosData {
 
         now
     }
+
+    pub fn write_rtc(mut self, century_register: u8) {
+        let register_b: u8 = get_cmos(0x0B);
+
+        self.split_year();
+        self.convert_24_hour_to_12_hour(register_b);
+        self.convert_binary_to_bcd(register_b);
+
+        // Inhibit the updates by setting bit7 in register_b, so that the registers are not
+        // updated while they are being written.
+        set_cmos(0x0B, register_b | 0x80);
+
+        set_cmos(0x00, self.second);
+        set_cmos(0x02, self.minute);
+        set_cmos(0x04, self.hour);
+        set_cmos(0x07, self.day);
+        set_cmos(0x08, self.month);
+        set_cmos(0x09, self.year as u8);
+        if century_register != 0 {
+            set_cmos(century_register, self.century);
+        }
+
+        set_cmos(0x0B, register_b & !0x80);
+    }
+}
+
+impl From<&SystemTime> for CmosData {
+    fn from(time: &SystemTime) -> CmosData {
+        CmosData {
+            century: 0,
+            year: time.year,
+            month: time.month,
+            day: time.day,
+            hour: time.hour,
+            minute: time.minute,
+            second: time.second,
+        }
+    }
 }
 
 impl From<CmosData> for SystemTime {
@@ -129,6 +211,12 @@ pub struct RtcCmos {
 
 impl Driver for RtcCmos {
     fn try_new() -> Option<RtcCmos> {
+        // Like Linux, the RTC is considered absent if register A reads as all ones, which is
+        // what reading a port without any device returns.
+        if get_cmos(0x0A) == 0xFF {
+            return None;
+        }
+
         Some(RtcCmos {
             century_register: century_register().unwrap_or(0),
         })
@@ -137,4 +225,19 @@ impl Driver for RtcCmos {
     fn read_rtc(&self) -> SystemTime {
         CmosData::read_rtc(self.century_register).into()
     }
+
+    fn write_rtc(&self, time: SystemTime) -> Result<(), RtcError> {
+        // Without the century register, the time is read as in the 21st century.
+        let years = if self.century_register != 0 {
+            0..=9999
+        } else {
+            2000..=2099
+        };
+        if !years.contains(&time.year) {
+            return Err(RtcError::InvalidTime);
+        }
+
+        CmosData::from(&time).write_rtc(self.century_register);
+        Ok(())
+    }
 }
@@ -1,9 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use ostd::{arch::timer::GOLDFISH_IO_MEM, mm::VmIoOnce};
-use chrono::{DateTime, Datelike, Timelike};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike};
 
-use crate::{SystemTime, rtc::Driver};
+use crate::{RtcError, SystemTime, rtc::Driver};
+
+const TIME_LOW: usize = 0;
+const TIME_HIGH: usize = 4;
 
 pub struct RtcGoldfish;
 
@@ -14,9 +17,6 @@ impl Driver for RtcGoldfish {
     }
 
     fn read_rtc(&self) -> SystemTime {
-        const TIME_LOW: usize = 0;
-        const TIME_HIGH: usize = 4;
-
         let io_mem = GOLDFISH_IO_MEM.get().unwrap();
 
         let mut last_time_high = io_mem.read_once(TIME_HIGH).unwrap();
@@ -43,4 +43,27 @@ impl Driver for RtcGoldfish {
             nanos: time.nanosecond() as u64,
         }
     }
+
+    fn write_rtc(&self, time: SystemTime) -> Result<(), RtcError> {
+        let date = NaiveDate::from_ymd_opt(time.year as i32, time.month as u32, time.day as u32);
+        let timestamp = date
+            .and_then(|date| {
+                date.and_hms_nano_opt(
+                    time.hour as u32,
+                    time.minute as u32,
+                    time.second as u32,
+                    time.nanos as u32,
+                )
+            })
+            .and_then(|time| time.and_utc().timestamp_nanos_opt())
+            .filter(|timestamp| *timestamp >= 0)
+            .ok_or(RtcError::InvalidTime)? as u64;
+
+        let io_mem = GOLDFISH_IO_MEM.get().unwrap();
+
+        // The device latches the time when the lower half is written.
+        io_mem.write_once(TIME_HIGH, &((timestamp >> 32) as u32)).unwrap();
+        io_mem.write_once(TIME_LOW, &(timestamp as u32)).unwrap();
+        Ok(())
+    }
 }
@@ -2,7 +2,7 @@
 
 use alloc::sync::Arc;
 
-use crate::SystemTime;
+use crate::{RtcError, SystemTime};
 
 /// Generic interface for RTC drivers
 pub trait Driver {
@@ -14,6 +14,11 @@ pub trait Driver {
 
     /// Reads RTC.
     fn read_rtc(&self) -> SystemTime;
+
+    /// Writes RTC.
+    ///
+    /// Returns [`RtcError::InvalidTime`] if the time cannot be represented by the RTC.
+    fn write_rtc(&self, time: SystemTime) -> Result<(), RtcError>;
 }
 
 macro_rules! declare_rtc_drivers {
@@ -37,6 +42,10 @@ macro_rules! declare_rtc_drivers {
     }
 }
 
+// TODO: Fall back to the EFI `GetTime` runtime service if there is no CMOS RTC. The EFI stub in
+// `linux-bzimage-setup` exits the boot services without keeping the runtime services mapped, so
+// the kernel cannot call `GetTime` now. Either the runtime services need to be mapped, or the stub
+// needs to read the time before exiting the boot services and pass it to the kernel.
 declare_rtc_drivers! {
     #[cfg(target_arch = "x86_64")] cmos::RtcCmos,
     #[cfg(target_arch = "riscv64")] goldfish::RtcGoldfish,
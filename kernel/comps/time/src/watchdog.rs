@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The clocksource watchdog.
+//!
+//! A clocksource may be unreliable even if it claims to be. For example, the TSC may
+//! stop in deep C-states on old hardware, or may be neither constant nor synchronized
+//! among CPUs on some virtual machines. The watchdog periodically compares the time
+//! elapsed on the current clocksource with that on a reference clocksource, which is
+//! the best stable clocksource with a lower rating (e.g., the HPET or the ACPI PM timer
+//! for the TSC). If they differ too much, the current clocksource is marked as unstable
+//! and the registry will switch to a fallback clocksource.
+
+use alloc::sync::Arc;
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::{clocksource::ClockSource, registry, NANOS_PER_SECOND};
+
+/// The interval (in timer ticks) between two checks.
+pub(crate) const INTERVAL_TICKS: u64 = TIMER_FREQ / 2;
+
+/// The maximum difference allowed between the elapsed time on the two clocksources
+/// in an interval, which is the same as Linux's `WATCHDOG_THRESHOLD`.
+const THRESHOLD_NANOS: u64 = NANOS_PER_SECOND as u64 >> 4;
+
+/// The maximum time allowed to read both clocksources.
+///
+/// If reading takes longer (e.g., the virtual CPU is preempted by the host), the two
+/// readings do not correspond to the same moment and cannot be compared.
+const MAX_READ_SKEW_NANOS: u64 = 100_000;
+
+/// The maximum number of attempts to read both clocksources.
+const MAX_READ_RETRIES: usize = 3;
+
+struct WatchdogState {
+    clock: Arc<ClockSource>,
+    reference: Arc<ClockSource>,
+    last_cycles: u64,
+    last_reference_cycles: u64,
+}
+
+static STATE: SpinLock<Option<WatchdogState>, LocalIrqDisabled> = SpinLock::new(None);
+
+/// Checks whether `clock` is stable.
+///
+/// This method should be called every [`INTERVAL_TICKS`] timer ticks.
+pub(crate) fn check(clock: &Arc<ClockSource>) {
+    let mut state = STATE.lock();
+
+    if !clock.is_stable() {
+        *state = None;
+        return;
+    }
+    let Some(reference) = registry::best_stable(|other| other.rating() < clock.rating()) else {
+        *state = None;
+        return;
+    };
+    let Some((cycles, reference_cycles)) = read_both(clock, &reference) else {
+        return;
+    };
+
+    let Some(last) = state
+        .as_mut()
+        .filter(|last| Arc::ptr_eq(&last.clock, clock) && Arc::ptr_eq(&last.reference, &reference))
+    else {
+        // The current clocksource or the reference has changed. Start over.
+        *state = Some(WatchdogState {
+            clock: clock.clone(),
+            reference,
+            last_cycles: cycles,
+            last_reference_cycles: reference_cycles,
+        });
+        return;
+    };
+
+    let nanos = cycles_to_nanos(clock, clock.delta_cycles(last.last_cycles, cycles));
+    let reference_nanos = cycles_to_nanos(
+        &reference,
+        reference.delta_cycles(last.last_reference_cycles, reference_cycles),
+    );
+    last.last_cycles = cycles;
+    last.last_reference_cycles = reference_cycles;
+
+    // If the check has been delayed for too long (e.g., local IRQs have been disabled),
+    // the counter of the reference may have wrapped around. Skip this interval.
+    if nanos >= cycles_to_nanos(&reference, reference.mask() / 2) {
+        return;
+    }
+
+    if nanos.abs_diff(reference_nanos) > THRESHOLD_NANOS {
+        log::warn!(
+            "clocksource: '{}' is unstable: {} ns elapsed on it, but {} ns elapsed on '{}'",
+            clock.name(),
+            nanos,
+            reference_nanos,
+            reference.name()
+        );
+        clock.mark_unstable();
+        *state = None;
+    }
+}
+
+/// Reads the cycles of `clock` and `reference` at the same moment.
+fn read_both(clock: &ClockSource, reference: &ClockSource) -> Option<(u64, u64)> {
+    for _ in 0..MAX_READ_RETRIES {
+        let reference_before = reference.read_cycles();
+        let cycles = clock.read_cycles();
+        let reference_after = reference.read_cycles();
+
+        let skew = reference.delta_cycles(reference_before, reference_after);
+        if cycles_to_nanos(reference, skew) <= MAX_READ_SKEW_NANOS {
+            return Some((cycles, reference_before));
+        }
+    }
+
+    None
+}
+
+fn cycles_to_nanos(clock: &ClockSource, cycles: u64) -> u64 {
+    let nanos = cycles as u128 * NANOS_PER_SECOND as u128 / clock.freq() as u128;
+    u64::try_from(nanos).unwrap_or(u64::MAX)
+}
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the HID boot keyboards and mice.
+//!
+//! The devices are switched to the boot protocol, whose reports have fixed formats, so the
+//! report descriptors are not parsed. The reports are polled by the interrupt transfers, and
+//! the differences between the consecutive reports are converted to the input events.
+//!
+//! Reference: Device Class Definition for Human Interface Devices (HID), Version 1.11,
+//! Appendix B (Boot Interface Descriptors).
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use aster_input::{
+    key::{Key, KeyStatus},
+    InputCapability, InputEvent, RelAxis,
+};
+use log::{info, warn};
+use ostd::{
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo},
+    sync::{LocalIrqDisabled, RwLock, SpinLock},
+};
+
+use crate::{
+    descriptor::{EndpointDescriptor, InterfaceDescriptor, TransferType},
+    device::{request_type, UsbDevice},
+    xhci::ring::{completion_code, Trb},
+    UsbError,
+};
+
+const HID_CLASS: u8 = 0x03;
+const BOOT_SUBCLASS: u8 = 0x01;
+const PROTOCOL_KEYBOARD: u8 = 0x01;
+const PROTOCOL_MOUSE: u8 = 0x02;
+
+// The class-specific requests.
+const SET_IDLE: u8 = 0x0A;
+const SET_PROTOCOL: u8 = 0x0B;
+const BOOT_PROTOCOL: u16 = 0;
+
+/// The maximum length of a report that is handled.
+const MAX_REPORT_LEN: usize = 8;
+
+static NR_DEVICES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns whether the interface is a boot keyboard or a boot mouse.
+pub(crate) fn matches(interface: &InterfaceDescriptor) -> bool {
+    interface.class == HID_CLASS
+        && interface.subclass == BOOT_SUBCLASS
+        && matches!(interface.protocol, PROTOCOL_KEYBOARD | PROTOCOL_MOUSE)
+}
+
+/// Sets up the interface and registers it to the input layer.
+pub(crate) fn probe(
+    device: &Arc<UsbDevice>,
+    interface: &InterfaceDescriptor,
+) -> Result<(), UsbError> {
+    let endpoint = *interface
+        .endpoints
+        .iter()
+        .find(|endpoint| endpoint.is_in() && endpoint.transfer_type() == TransferType::Interrupt)
+        .ok_or(UsbError::InvalidResponse)?;
+    let kind = if interface.protocol == PROTOCOL_KEYBOARD {
+        HidKind::Keyboard
+    } else {
+        HidKind::Mouse
+    };
+
+    let class_interface = request_type::CLASS | request_type::INTERFACE;
+    device.control_out(
+        class_interface,
+        SET_PROTOCOL,
+        BOOT_PROTOCOL,
+        interface.number as u16,
+        &[],
+    )?;
+    // Only report the changes. Some devices do not support the request, which is fine.
+    let _ = device.control_out(class_interface, SET_IDLE, 0, interface.number as u16, &[]);
+    device.configure_endpoints(&[endpoint])?;
+
+    let report_buf = {
+        let segment = FrameAllocOptions::new()
+            .alloc_segment(1)
+            .map_err(|_| UsbError::NoMemory)?;
+        DmaCoherent::map(segment.into(), true).map_err(|_| UsbError::NoMemory)?
+    };
+    let name = format!("usbhid{}", NR_DEVICES.fetch_add(1, Ordering::Relaxed));
+    let hid_device = Arc::new(UsbHidDevice {
+        name: name.clone(),
+        device: device.clone(),
+        kind,
+        endpoint,
+        report_len: (endpoint.max_packet_size as usize).clamp(1, MAX_REPORT_LEN),
+        report_buf,
+        last_report: SpinLock::new([0; MAX_REPORT_LEN]),
+        callbacks: RwLock::new(Vec::new()),
+    });
+
+    let cloned_device = hid_device.clone();
+    device.set_interrupt_handler(
+        &endpoint,
+        Box::new(move |event| cloned_device.handle_transfer(event)),
+    );
+    hid_device.submit()?;
+
+    info!("[USB]: Slot {}: {:?} {}", device.slot_id(), kind, name);
+    aster_input::register_device(name, hid_device);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HidKind {
+    Keyboard,
+    Mouse,
+}
+
+/// A HID boot keyboard or a HID boot mouse.
+pub struct UsbHidDevice {
+    name: String,
+    device: Arc<UsbDevice>,
+    kind: HidKind,
+    endpoint: EndpointDescriptor,
+    report_len: usize,
+    report_buf: DmaCoherent,
+    last_report: SpinLock<[u8; MAX_REPORT_LEN]>,
+    callbacks: RwLock<Vec<Arc<dyn Fn(InputEvent) + Send + Sync + 'static>>, LocalIrqDisabled>,
+}
+
+impl UsbHidDevice {
+    fn submit(&self) -> Result<(), UsbError> {
+        self.device.submit_interrupt_transfer(
+            &self.endpoint,
+            self.report_buf.daddr(),
+            self.report_len,
+        )
+    }
+
+    fn handle_transfer(&self, event: &Trb) {
+        match event.completion_code() {
+            completion_code::SUCCESS | completion_code::SHORT_PACKET => (),
+            code => {
+                // The halted endpoint cannot be recovered in the interrupt context.
+                warn!(
+                    "[USB]: {}: The interrupt transfer fails with {}, stop polling",
+                    self.name, code
+                );
+                return;
+            }
+        }
+
+        let mut report = [0u8; MAX_REPORT_LEN];
+        let len = self.report_len.saturating_sub(event.residual_len());
+        self.report_buf.read_bytes(0, &mut report[..len]).unwrap();
+
+        {
+            let mut last_report = self.last_report.disable_irq().lock();
+            let callbacks = self.callbacks.read();
+            let mut emit = |event: InputEvent| {
+                for callback in callbacks.iter() {
+                    callback(event);
+                }
+            };
+            match self.kind {
+                HidKind::Keyboard => handle_keyboard_report(&last_report, &report, &mut emit),
+                HidKind::Mouse => handle_mouse_report(&last_report, &report[..len], &mut emit),
+            }
+            emit(InputEvent::Sync);
+            *last_report = report;
+        }
+
+        if let Err(err) = self.submit() {
+            warn!(
+                "[USB]: {}: Failed to poll the reports: {:?}",
+                self.name, err
+            );
+        }
+    }
+}
+
+/// The key codes of the modifier bits in the keyboard reports.
+const MODIFIER_KEYS: [Key; 8] = [
+    Key::LeftCtrl,
+    Key::LeftShift,
+    Key::LeftAlt,
+    Key::LeftMeta,
+    Key::RightCtrl,
+    Key::RightShift,
+    Key::RightAlt,
+    Key::RightMeta,
+];
+
+/// The key codes of the HID usages in the keyboard page, from usage 0x00 to usage 0x63.
+///
+/// The codes that are not supported by the input layer are 0.
+#[rustfmt::skip]
+const USAGE_TO_KEY_CODE: [u16; 100] = [
+    0, 0, 0, 0, 30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38,
+    50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45, 21, 44, 2, 3,
+    4, 5, 6, 7, 8, 9, 10, 11, 28, 1, 14, 15, 57, 12, 13, 26,
+    27, 43, 43, 39, 40, 41, 51, 52, 53, 58, 59, 60, 61, 62, 63, 64,
+    65, 66, 67, 68, 87, 88, 0, 70, 0, 110, 102, 104, 111, 107, 109, 106,
+    105, 108, 103, 69, 98, 55, 74, 78, 96, 79, 80, 81, 75, 76, 77, 71,
+    72, 73, 82, 83,
+];
+
+fn usage_to_key(usage: u8) -> Option<Key> {
+    let code = *USAGE_TO_KEY_CODE.get(usage as usize)?;
+    if code == 0 {
+        return None;
+    }
+    Key::try_from(code).ok()
+}
+
+/// Converts the keyboard report to the key events.
+///
+/// The report consists of the modifier bits, a reserved byte, and up to six pressed keys.
+fn handle_keyboard_report(
+    last: &[u8; MAX_REPORT_LEN],
+    report: &[u8; MAX_REPORT_LEN],
+    emit: &mut impl FnMut(InputEvent),
+) {
+    // The keys are reported as `ErrorRollOver` (0x01) if too many keys are pressed.
+    if report[2..].contains(&0x01) {
+        return;
+    }
+
+    let changed_modifiers = last[0] ^ report[0];
+    for (bit, key) in MODIFIER_KEYS.iter().enumerate() {
+        if changed_modifiers & (1 << bit) != 0 {
+            let status = if report[0] & (1 << bit) != 0 {
+                KeyStatus::Pressed
+            } else {
+                KeyStatus::Released
+            };
+            emit(InputEvent::KeyBoard(*key, status));
+        }
+    }
+
+    for &usage in last[2..].iter() {
+        if usage != 0 && !report[2..].contains(&usage) {
+            if let Some(key) = usage_to_key(usage) {
+                emit(InputEvent::KeyBoard(key, KeyStatus::Released));
+            }
+        }
+    }
+    for &usage in report[2..].iter() {
+        if usage != 0 && !last[2..].contains(&usage) {
+            if let Some(key) = usage_to_key(usage) {
+                emit(InputEvent::KeyBoard(key, KeyStatus::Pressed));
+            }
+        }
+    }
+}
+
+/// The key codes of the button bits in the mouse reports.
+const BUTTON_KEYS: [Key; 3] = [Key::BtnLeft, Key::BtnRight, Key::BtnMiddle];
+
+/// Converts the mouse report to the button events and the motion events.
+///
+/// The report consists of the button bits, the X and Y displacements, and optionally the wheel
+/// displacement.
+fn handle_mouse_report(
+    last: &[u8; MAX_REPORT_LEN],
+    report: &[u8],
+    emit: &mut impl FnMut(InputEvent),
+) {
+    if report.len() < 3 {
+        return;
+    }
+
+    let changed_buttons = last[0] ^ report[0];
+    for (bit, key) in BUTTON_KEYS.iter().enumerate() {
+        if changed_buttons & (1 << bit) != 0 {
+            let status = if report[0] & (1 << bit) != 0 {
+                KeyStatus::Pressed
+            } else {
+                KeyStatus::Released
+            };
+            emit(InputEvent::KeyBoard(*key, status));
+        }
+    }
+
+    let motions = [
+        (RelAxis::X, report[1]),
+        (RelAxis::Y, report[2]),
+        (RelAxis::Wheel, report.get(3).copied().unwrap_or(0)),
+    ];
+    for (axis, value) in motions {
+        if value != 0 {
+            emit(InputEvent::Relative(axis, value as i8 as i32));
+        }
+    }
+}
+
+impl aster_input::InputDevice for UsbHidDevice {
+    fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync)) {
+        self.callbacks.write().push(Arc::new(function))
+    }
+
+    fn capability(&self) -> InputCapability {
+        match self.kind {
+            HidKind::Keyboard => InputCapability::keyboard(),
+            HidKind::Mouse => InputCapability::mouse(),
+        }
+    }
+}
+
+impl Debug for UsbHidDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UsbHidDevice")
+            .field("name", &self.name)
+            .field("device", &self.device)
+            .field("kind", &self.kind)
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The class drivers of USB devices.
+
+pub(crate) mod hid;
+pub(crate) mod storage;
+
+use alloc::sync::Arc;
+
+use log::{debug, warn};
+
+use crate::device::UsbDevice;
+
+/// Binds the class drivers to the interfaces of the device.
+pub(crate) fn probe(device: &Arc<UsbDevice>) {
+    for interface in device.config().interfaces.iter() {
+        let result = if hid::matches(interface) {
+            hid::probe(device, interface)
+        } else if storage::matches(interface) {
+            storage::probe(device, interface)
+        } else {
+            debug!(
+                "[USB]: Slot {}: No driver for interface {} (class {:#x}, subclass {:#x}, protocol {:#x})",
+                device.slot_id(),
+                interface.number,
+                interface.class,
+                interface.subclass,
+                interface.protocol
+            );
+            continue;
+        };
+
+        if let Err(err) = result {
+            warn!(
+                "[USB]: Slot {}: Failed to set up interface {}: {:?}",
+                device.slot_id(),
+                interface.number,
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,564 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the bulk-only mass storage devices.
+//!
+//! Each SCSI command is wrapped in a Command Block Wrapper (CBW) that is sent to the bulk OUT
+//! endpoint, followed by an optional data phase, and a Command Status Wrapper (CSW) that is read
+//! from the bulk IN endpoint. The transport can only carry one command at a time, so the bios are
+//! transferred synchronously in the context that submits them.
+//!
+//! Only the first logical unit of each device is used.
+//!
+//! Reference: Universal Serial Bus Mass Storage Class Bulk-Only Transport, Revision 1.0.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    BlockDeviceMeta, SECTOR_SIZE,
+};
+use log::{info, warn};
+use ostd::{
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo},
+    sync::{Mutex, SpinLock},
+    Pod,
+};
+
+use crate::{
+    descriptor::{EndpointDescriptor, InterfaceDescriptor, TransferType},
+    device::{request, request_type, UsbDevice},
+    UsbError,
+};
+
+const MASS_STORAGE_CLASS: u8 = 0x08;
+const SCSI_SUBCLASS: u8 = 0x06;
+const BULK_ONLY_PROTOCOL: u8 = 0x50;
+
+// The class-specific requests.
+const BULK_ONLY_RESET: u8 = 0xFF;
+
+/// The feature selector that clears the halt condition of an endpoint.
+const ENDPOINT_HALT: u16 = 0;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CBW_FLAG_IN: u8 = 0x80;
+const CBW_LEN: usize = 31;
+const CSW_LEN: usize = 13;
+
+// The status of the CSWs. The other status is a phase error.
+const CSW_STATUS_PASSED: u8 = 0;
+const CSW_STATUS_FAILED: u8 = 1;
+
+// The SCSI commands.
+const TEST_UNIT_READY: u8 = 0x00;
+const REQUEST_SENSE: u8 = 0x03;
+const READ_CAPACITY_10: u8 = 0x25;
+const READ_10: u8 = 0x28;
+const WRITE_10: u8 = 0x2A;
+const SYNCHRONIZE_CACHE_10: u8 = 0x35;
+const READ_16: u8 = 0x88;
+const WRITE_16: u8 = 0x8A;
+const SERVICE_ACTION_IN_16: u8 = 0x9E;
+const READ_CAPACITY_16: u8 = 0x10;
+
+/// The maximum number of bytes that are transferred by one command.
+const MAX_TRANSFER_LEN: usize = 64 * 1024;
+/// The time that a command takes to finish, including the time to spin up the medium.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+/// The number of times that the device is polled until the medium is ready.
+const NR_READY_RETRIES: usize = 10;
+
+/// The offset of the CSW in the command buffer.
+const CSW_OFFSET: usize = 64;
+/// The offset of the data of the internal commands in the command buffer.
+const DATA_OFFSET: usize = 128;
+
+static STORAGE_DEVICES: SpinLock<Vec<Arc<UsbStorage>>> = SpinLock::new(Vec::new());
+static NR_DEVICES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns all the USB mass storage devices.
+pub(crate) fn all_devices() -> Vec<Arc<UsbStorage>> {
+    STORAGE_DEVICES.lock().clone()
+}
+
+/// Returns whether the interface is a bulk-only mass storage device with the SCSI commands.
+pub(crate) fn matches(interface: &InterfaceDescriptor) -> bool {
+    interface.class == MASS_STORAGE_CLASS
+        && interface.subclass == SCSI_SUBCLASS
+        && interface.protocol == BULK_ONLY_PROTOCOL
+}
+
+/// Sets up the interface and registers it to the block layer.
+pub(crate) fn probe(
+    device: &Arc<UsbDevice>,
+    interface: &InterfaceDescriptor,
+) -> Result<(), UsbError> {
+    let find_endpoint = |is_in: bool| {
+        interface
+            .endpoints
+            .iter()
+            .find(|endpoint| {
+                endpoint.is_in() == is_in && endpoint.transfer_type() == TransferType::Bulk
+            })
+            .copied()
+            .ok_or(UsbError::InvalidResponse)
+    };
+    let bulk_in = find_endpoint(true)?;
+    let bulk_out = find_endpoint(false)?;
+    device.configure_endpoints(&[bulk_in, bulk_out])?;
+
+    let command_buf = {
+        let segment = FrameAllocOptions::new()
+            .alloc_segment(1)
+            .map_err(|_| UsbError::NoMemory)?;
+        DmaCoherent::map(segment.into(), true).map_err(|_| UsbError::NoMemory)?
+    };
+    let mut storage = UsbStorage {
+        name: device_name(NR_DEVICES.fetch_add(1, Ordering::Relaxed)),
+        device: device.clone(),
+        interface: interface.number,
+        bulk_in,
+        bulk_out,
+        command_buf: Mutex::new(command_buf),
+        next_tag: AtomicU32::new(1),
+        block_size: 0,
+        nr_blocks: 0,
+    };
+
+    storage.wait_until_ready()?;
+    let (block_size, nr_blocks) = storage.read_capacity()?;
+    if block_size == 0 || block_size % SECTOR_SIZE != 0 {
+        warn!(
+            "[USB]: {}: The block size {} is not supported",
+            storage.name, block_size
+        );
+        return Err(UsbError::NotSupported);
+    }
+    storage.block_size = block_size;
+    storage.nr_blocks = nr_blocks;
+
+    info!(
+        "[USB]: Slot {}: Mass storage {} ({} blocks of {} bytes)",
+        device.slot_id(),
+        storage.name,
+        nr_blocks,
+        block_size
+    );
+    let storage = Arc::new(storage);
+    aster_block::register_device(storage.name.clone(), storage.clone());
+    STORAGE_DEVICES.lock().push(storage);
+    Ok(())
+}
+
+/// Returns the name of the `index`-th device (e.g., `sda`, `sdz`, and `sdaa`).
+fn device_name(mut index: usize) -> String {
+    let mut suffix = Vec::new();
+    loop {
+        suffix.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    suffix.reverse();
+
+    let mut name = String::from("sd");
+    name.push_str(core::str::from_utf8(&suffix).unwrap());
+    name
+}
+
+/// The direction and the buffer of the data phase.
+#[derive(Debug, Clone, Copy)]
+enum DataPhase {
+    None,
+    In { daddr: usize, len: usize },
+    Out { daddr: usize, len: usize },
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CommandBlockWrapper {
+    signature: u32,
+    tag: u32,
+    data_transfer_len: u32,
+    flags: u8,
+    lun: u8,
+    command_len: u8,
+    command: [u8; 16],
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CommandStatusWrapper {
+    signature: u32,
+    tag: u32,
+    #[expect(dead_code)]
+    data_residue: u32,
+    status: u8,
+}
+
+/// A USB mass storage device.
+pub struct UsbStorage {
+    name: String,
+    device: Arc<UsbDevice>,
+    interface: u8,
+    bulk_in: EndpointDescriptor,
+    bulk_out: EndpointDescriptor,
+    /// The buffer of the CBWs, the CSWs, and the data of the internal commands.
+    ///
+    /// The lock also serializes the commands, since the transport cannot carry more than one.
+    command_buf: Mutex<DmaCoherent>,
+    next_tag: AtomicU32,
+    block_size: usize,
+    nr_blocks: u64,
+}
+
+impl UsbStorage {
+    /// Returns the name of the device (e.g., `sda`).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the logical blocks in bytes.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the USB device.
+    pub fn device(&self) -> &Arc<UsbDevice> {
+        &self.device
+    }
+
+    /// Polls the device until the medium is ready.
+    fn wait_until_ready(&self) -> Result<(), UsbError> {
+        let mut command = [0u8; 6];
+        command[0] = TEST_UNIT_READY;
+
+        for _ in 0..NR_READY_RETRIES {
+            if self.execute_internal(&command, None)? {
+                return Ok(());
+            }
+            // Clear the unit attention condition, which is reported after the device is reset
+            // or the medium is changed.
+            let mut sense = [0u8; 18];
+            let mut command = [0u8; 6];
+            command[0] = REQUEST_SENSE;
+            command[4] = sense.len() as u8;
+            self.execute_internal(&command, Some(&mut sense))?;
+            crate::delay(Duration::from_millis(100));
+        }
+
+        Err(UsbError::Timeout)
+    }
+
+    /// Returns the block size and the number of the blocks.
+    fn read_capacity(&self) -> Result<(usize, u64), UsbError> {
+        let mut command = [0u8; 10];
+        command[0] = READ_CAPACITY_10;
+        let mut data = [0u8; 8];
+        if !self.execute_internal(&command, Some(&mut data))? {
+            return Err(UsbError::InvalidResponse);
+        }
+        let last_lba = u32::from_be_bytes(data[0..4].try_into().unwrap());
+        let block_size = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        if last_lba != u32::MAX {
+            return Ok((block_size as usize, last_lba as u64 + 1));
+        }
+
+        // The capacity does not fit in 32 bits.
+        let mut command = [0u8; 16];
+        command[0] = SERVICE_ACTION_IN_16;
+        command[1] = READ_CAPACITY_16;
+        let mut data = [0u8; 32];
+        command[13] = data.len() as u8;
+        if !self.execute_internal(&command, Some(&mut data))? {
+            return Err(UsbError::InvalidResponse);
+        }
+        let last_lba = u64::from_be_bytes(data[0..8].try_into().unwrap());
+        let block_size = u32::from_be_bytes(data[8..12].try_into().unwrap());
+        Ok((block_size as usize, last_lba + 1))
+    }
+
+    /// Executes a command whose data are transferred through the command buffer.
+    ///
+    /// Returns whether the command passes.
+    fn execute_internal(
+        &self,
+        command: &[u8],
+        data_in: Option<&mut [u8]>,
+    ) -> Result<bool, UsbError> {
+        let command_buf = self.command_buf.lock();
+        let data_phase = match data_in.as_ref() {
+            Some(data) => DataPhase::In {
+                daddr: command_buf.daddr() + DATA_OFFSET,
+                len: data.len(),
+            },
+            None => DataPhase::None,
+        };
+        let passed = self.execute(&command_buf, command, data_phase)?;
+        if let Some(data) = data_in {
+            command_buf.read_bytes(DATA_OFFSET, data).unwrap();
+        }
+        Ok(passed)
+    }
+
+    /// Executes a command with the BOT protocol.
+    ///
+    /// Returns whether the command passes. Failed commands are not errors of the transport.
+    fn execute(
+        &self,
+        command_buf: &DmaCoherent,
+        command: &[u8],
+        data_phase: DataPhase,
+    ) -> Result<bool, UsbError> {
+        let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+        let (flags, data_transfer_len) = match data_phase {
+            DataPhase::None => (0, 0),
+            DataPhase::In { len, .. } => (CBW_FLAG_IN, len),
+            DataPhase::Out { len, .. } => (0, len),
+        };
+        let mut cbw = CommandBlockWrapper {
+            signature: CBW_SIGNATURE,
+            tag,
+            data_transfer_len: data_transfer_len as u32,
+            flags,
+            lun: 0,
+            command_len: command.len() as u8,
+            command: [0; 16],
+        };
+        cbw.command[..command.len()].copy_from_slice(command);
+        command_buf.write_val(0, &cbw).unwrap();
+
+        let result = self.transfer_phases(command_buf, data_phase);
+        let csw = match result {
+            Ok(csw) => csw,
+            Err(err) => {
+                self.reset_recovery();
+                return Err(err);
+            }
+        };
+
+        let (signature, csw_tag) = (csw.signature, csw.tag);
+        match csw.status {
+            CSW_STATUS_PASSED if signature == CSW_SIGNATURE && csw_tag == tag => Ok(true),
+            CSW_STATUS_FAILED if signature == CSW_SIGNATURE && csw_tag == tag => Ok(false),
+            // The CSW is invalid or reports a phase error.
+            _ => {
+                self.reset_recovery();
+                Err(UsbError::InvalidResponse)
+            }
+        }
+    }
+
+    /// Sends the CBW, transfers the data, and receives the CSW.
+    fn transfer_phases(
+        &self,
+        command_buf: &DmaCoherent,
+        data_phase: DataPhase,
+    ) -> Result<CommandStatusWrapper, UsbError> {
+        let daddr = command_buf.daddr();
+        self.device
+            .bulk_transfer(&self.bulk_out, daddr, CBW_LEN, COMMAND_TIMEOUT)?;
+
+        let data_result = match data_phase {
+            DataPhase::None => Ok(0),
+            DataPhase::In { daddr, len } => {
+                self.device
+                    .bulk_transfer(&self.bulk_in, daddr, len, COMMAND_TIMEOUT)
+            }
+            DataPhase::Out { daddr, len } => {
+                self.device
+                    .bulk_transfer(&self.bulk_out, daddr, len, COMMAND_TIMEOUT)
+            }
+        };
+        // A stalled data phase is followed by the CSW, after the halt is cleared.
+        match data_result {
+            Ok(_) | Err(UsbError::Stall) => (),
+            Err(err) => return Err(err),
+        }
+
+        let csw_daddr = daddr + CSW_OFFSET;
+        match self
+            .device
+            .bulk_transfer(&self.bulk_in, csw_daddr, CSW_LEN, COMMAND_TIMEOUT)
+        {
+            // The CSW can be read again after the halt is cleared.
+            Err(UsbError::Stall) => {
+                self.device
+                    .bulk_transfer(&self.bulk_in, csw_daddr, CSW_LEN, COMMAND_TIMEOUT)?;
+            }
+            result => {
+                result?;
+            }
+        }
+
+        Ok(command_buf.read_val(CSW_OFFSET).unwrap())
+    }
+
+    /// Resets the device and clears the halt conditions of the bulk endpoints.
+    fn reset_recovery(&self) {
+        warn!("[USB]: {}: Resetting the device", self.name);
+
+        let result = self
+            .device
+            .control_out(
+                request_type::CLASS | request_type::INTERFACE,
+                BULK_ONLY_RESET,
+                0,
+                self.interface as u16,
+                &[],
+            )
+            .and_then(|_| {
+                self.device.control_out(
+                    request_type::ENDPOINT,
+                    request::CLEAR_FEATURE,
+                    ENDPOINT_HALT,
+                    self.bulk_in.address as u16,
+                    &[],
+                )
+            })
+            .and_then(|_| {
+                self.device.control_out(
+                    request_type::ENDPOINT,
+                    request::CLEAR_FEATURE,
+                    ENDPOINT_HALT,
+                    self.bulk_out.address as u16,
+                    &[],
+                )
+            });
+        if let Err(err) = result {
+            warn!(
+                "[USB]: {}: Failed to reset the device: {:?}",
+                self.name, err
+            );
+        }
+    }
+
+    /// Builds the READ or WRITE command of the blocks.
+    fn build_read_write(&self, is_write: bool, lba: u64, nr_blocks: u16) -> ([u8; 16], usize) {
+        let mut command = [0u8; 16];
+        if lba <= u32::MAX as u64 {
+            command[0] = if is_write { WRITE_10 } else { READ_10 };
+            command[2..6].copy_from_slice(&(lba as u32).to_be_bytes());
+            command[7..9].copy_from_slice(&nr_blocks.to_be_bytes());
+            (command, 10)
+        } else {
+            command[0] = if is_write { WRITE_16 } else { READ_16 };
+            command[2..10].copy_from_slice(&lba.to_be_bytes());
+            command[10..14].copy_from_slice(&(nr_blocks as u32).to_be_bytes());
+            (command, 16)
+        }
+    }
+
+    /// Transfers the data of the bio.
+    fn read_write(&self, bio: &SubmittedBio) -> BioStatus {
+        let is_write = bio.type_() == BioType::Write;
+        let start = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+        if start % self.block_size != 0 {
+            return BioStatus::IoError;
+        }
+        let mut lba = (start / self.block_size) as u64;
+
+        let command_buf = self.command_buf.lock();
+        for segment in bio.segments() {
+            let dma_slice = segment.inner_dma_slice();
+            let daddr = dma_slice.daddr();
+            let len = dma_slice.nbytes();
+            if len % self.block_size != 0 {
+                return BioStatus::IoError;
+            }
+
+            let mut offset = 0;
+            while offset < len {
+                let chunk_len = (len - offset).min(MAX_TRANSFER_LEN);
+                let nr_blocks = (chunk_len / self.block_size) as u16;
+                let (command, command_len) = self.build_read_write(is_write, lba, nr_blocks);
+                let data_phase = if is_write {
+                    DataPhase::Out {
+                        daddr: daddr + offset,
+                        len: chunk_len,
+                    }
+                } else {
+                    DataPhase::In {
+                        daddr: daddr + offset,
+                        len: chunk_len,
+                    }
+                };
+
+                match self.execute(&command_buf, &command[..command_len], data_phase) {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        warn!("[USB]: {}: The command fails at block {}", self.name, lba);
+                        return BioStatus::IoError;
+                    }
+                    Err(err) => {
+                        warn!("[USB]: {}: The transfer fails: {:?}", self.name, err);
+                        return BioStatus::IoError;
+                    }
+                }
+
+                offset += chunk_len;
+                lba += nr_blocks as u64;
+            }
+        }
+        drop(command_buf);
+
+        // Synchronize DMA mapping if read from the device
+        if !is_write {
+            bio.segments()
+                .iter()
+                .for_each(|segment| segment.inner_dma_slice().sync().unwrap());
+        }
+        BioStatus::Complete
+    }
+
+    fn flush(&self) -> BioStatus {
+        let mut command = [0u8; 10];
+        command[0] = SYNCHRONIZE_CACHE_10;
+        match self.execute_internal(&command, None) {
+            Ok(_) => BioStatus::Complete,
+            Err(err) => {
+                warn!("[USB]: {}: Failed to flush the cache: {:?}", self.name, err);
+                BioStatus::IoError
+            }
+        }
+    }
+}
+
+impl aster_block::BlockDevice for UsbStorage {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let status = match bio.type_() {
+            BioType::Read | BioType::Write => self.read_write(&bio),
+            // Many devices do not have a write cache and fail the command, which is fine.
+            BioType::Flush => self.flush(),
+            BioType::Discard | BioType::WriteZeroes => BioStatus::NotSupported,
+        };
+        bio.complete(status);
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            // The segments are transferred by separate commands.
+            max_nr_segments_per_bio: usize::MAX,
+            nr_sectors: (self.nr_blocks as usize * self.block_size) / SECTOR_SIZE,
+        }
+    }
+}
+
+impl Debug for UsbStorage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UsbStorage")
+            .field("name", &self.name)
+            .field("device", &self.device)
+            .field("block_size", &self.block_size)
+            .field("nr_blocks", &self.nr_blocks)
+            .finish_non_exhaustive()
+    }
+}
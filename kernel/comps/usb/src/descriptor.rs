@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The standard descriptors of USB devices.
+//!
+//! Reference: Universal Serial Bus Specification, Revision 2.0, Section 9.6 (Standard USB
+//! Descriptor Definitions).
+
+use alloc::vec::Vec;
+
+use crate::UsbError;
+
+/// The types of the descriptors.
+pub(crate) mod descriptor_type {
+    pub(crate) const DEVICE: u8 = 1;
+    pub(crate) const CONFIGURATION: u8 = 2;
+    pub(crate) const INTERFACE: u8 = 4;
+    pub(crate) const ENDPOINT: u8 = 5;
+    pub(crate) const HUB: u8 = 0x29;
+}
+
+/// The length of a device descriptor.
+pub(crate) const DEVICE_DESCRIPTOR_LEN: usize = 18;
+/// The length of the header of a configuration descriptor.
+pub(crate) const CONFIG_DESCRIPTOR_LEN: usize = 9;
+
+/// A device descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceDescriptor {
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub nr_configs: u8,
+}
+
+impl DeviceDescriptor {
+    pub(crate) fn parse(buf: &[u8]) -> Result<Self, UsbError> {
+        if buf.len() < DEVICE_DESCRIPTOR_LEN || buf[1] != descriptor_type::DEVICE {
+            return Err(UsbError::InvalidResponse);
+        }
+
+        Ok(Self {
+            usb_version: u16::from_le_bytes([buf[2], buf[3]]),
+            class: buf[4],
+            subclass: buf[5],
+            protocol: buf[6],
+            max_packet_size0: buf[7],
+            vendor_id: u16::from_le_bytes([buf[8], buf[9]]),
+            product_id: u16::from_le_bytes([buf[10], buf[11]]),
+            nr_configs: buf[17],
+        })
+    }
+}
+
+/// A configuration descriptor with its interfaces.
+#[derive(Debug, Clone)]
+pub(crate) struct ConfigDescriptor {
+    /// The value to select the configuration.
+    pub(crate) value: u8,
+    /// The interfaces, where only the default alternate settings are kept.
+    pub(crate) interfaces: Vec<InterfaceDescriptor>,
+}
+
+impl ConfigDescriptor {
+    /// Returns the total length of the configuration descriptor, which is parsed from its
+    /// header.
+    pub(crate) fn total_len(header: &[u8]) -> Result<usize, UsbError> {
+        if header.len() < CONFIG_DESCRIPTOR_LEN || header[1] != descriptor_type::CONFIGURATION {
+            return Err(UsbError::InvalidResponse);
+        }
+        Ok(u16::from_le_bytes([header[2], header[3]]) as usize)
+    }
+
+    /// Parses the configuration descriptor and the interface and endpoint descriptors that
+    /// follow it.
+    pub(crate) fn parse(buf: &[u8]) -> Result<Self, UsbError> {
+        let total_len = Self::total_len(buf)?.min(buf.len());
+        let value = buf[5];
+
+        let mut interfaces: Vec<InterfaceDescriptor> = Vec::new();
+        // Whether the endpoints belong to an alternate setting that is skipped.
+        let mut is_skipping = false;
+        let mut offset = buf[0] as usize;
+        while offset + 2 <= total_len {
+            let len = buf[offset] as usize;
+            if len < 2 || offset + len > total_len {
+                return Err(UsbError::InvalidResponse);
+            }
+            let desc = &buf[offset..offset + len];
+
+            match desc[1] {
+                descriptor_type::INTERFACE if len >= 9 => {
+                    is_skipping = desc[3] != 0;
+                    if !is_skipping {
+                        interfaces.push(InterfaceDescriptor {
+                            number: desc[2],
+                            class: desc[5],
+                            subclass: desc[6],
+                            protocol: desc[7],
+                            endpoints: Vec::new(),
+                        });
+                    }
+                }
+                descriptor_type::ENDPOINT if len >= 7 && !is_skipping => {
+                    if let Some(interface) = interfaces.last_mut() {
+                        interface.endpoints.push(EndpointDescriptor {
+                            address: desc[2],
+                            attributes: desc[3],
+                            max_packet_size: u16::from_le_bytes([desc[4], desc[5]]) & 0x7ff,
+                            interval: desc[6],
+                        });
+                    }
+                }
+                // Skip the class-specific descriptors and the other descriptors.
+                _ => (),
+            }
+
+            offset += len;
+        }
+
+        Ok(Self { value, interfaces })
+    }
+}
+
+/// An interface descriptor with its endpoints.
+#[derive(Debug, Clone)]
+pub struct InterfaceDescriptor {
+    pub number: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub endpoints: Vec<EndpointDescriptor>,
+}
+
+/// The transfer types of the endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferType {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+/// An endpoint descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointDescriptor {
+    /// The endpoint number and the direction (bit 7).
+    pub address: u8,
+    pub attributes: u8,
+    pub max_packet_size: u16,
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+
+    pub fn number(&self) -> u8 {
+        self.address & 0xf
+    }
+
+    pub fn transfer_type(&self) -> TransferType {
+        match self.attributes & 0x3 {
+            0 => TransferType::Control,
+            1 => TransferType::Isochronous,
+            2 => TransferType::Bulk,
+            _ => TransferType::Interrupt,
+        }
+    }
+
+    /// Returns the Device Context Index of the endpoint in xHCI.
+    pub(crate) fn dci(&self) -> u8 {
+        self.number() * 2 + self.is_in() as u8
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// The device descriptor of a USB 2.0 flash drive.
+    const DEVICE: [u8; DEVICE_DESCRIPTOR_LEN] = [
+        18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x81, 0x07, 0x81, 0x55, 0x00, 0x01, 1, 2, 3, 1,
+    ];
+
+    /// The configuration descriptor of a mass storage device, whose second interface has an
+    /// alternate setting and a class-specific descriptor.
+    const CONFIG: [u8; 62] = [
+        // Configuration.
+        9, 2, 62, 0, 2, 1, 0, 0x80, 50, //
+        // Interface 0, alternate setting 0.
+        9, 4, 0, 0, 2, 0x08, 0x06, 0x50, 0, //
+        // Endpoint 1 IN, bulk.
+        7, 5, 0x81, 0x02, 0x00, 0x02, 0, //
+        // Endpoint 2 OUT, bulk.
+        7, 5, 0x02, 0x02, 0x00, 0x02, 0, //
+        // Interface 1, alternate setting 0.
+        9, 4, 1, 0, 1, 0x03, 0x00, 0x00, 0, //
+        // A class-specific descriptor.
+        3, 0x21, 0, //
+        // Endpoint 3 IN, interrupt, with the high-bandwidth bits set.
+        7, 5, 0x83, 0x03, 0x08, 0x18, 10, //
+        // Interface 1, alternate setting 1, whose endpoints are skipped.
+        9, 4, 1, 1, 1, 0x03, 0x00, 0x00, 0, //
+        // An endpoint descriptor that is too short, which is ignored.
+        2, 5,
+    ];
+
+    #[ktest]
+    fn parse_device() {
+        let desc = DeviceDescriptor::parse(&DEVICE).unwrap();
+        assert_eq!(desc.usb_version, 0x0200);
+        assert_eq!(desc.max_packet_size0, 64);
+        assert_eq!(desc.vendor_id, 0x0781);
+        assert_eq!(desc.product_id, 0x5581);
+        assert_eq!(desc.nr_configs, 1);
+
+        assert_eq!(
+            DeviceDescriptor::parse(&DEVICE[..8]).unwrap_err(),
+            UsbError::InvalidResponse
+        );
+        let mut wrong_type = DEVICE;
+        wrong_type[1] = descriptor_type::CONFIGURATION;
+        assert_eq!(
+            DeviceDescriptor::parse(&wrong_type).unwrap_err(),
+            UsbError::InvalidResponse
+        );
+    }
+
+    #[ktest]
+    fn parse_config() {
+        assert_eq!(ConfigDescriptor::total_len(&CONFIG[..9]), Ok(62));
+        assert_eq!(
+            ConfigDescriptor::total_len(&CONFIG[..8]),
+            Err(UsbError::InvalidResponse)
+        );
+
+        let config = ConfigDescriptor::parse(&CONFIG).unwrap();
+        assert_eq!(config.value, 1);
+        assert_eq!(config.interfaces.len(), 2);
+
+        let storage = &config.interfaces[0];
+        assert_eq!(
+            (
+                storage.number,
+                storage.class,
+                storage.subclass,
+                storage.protocol
+            ),
+            (0, 0x08, 0x06, 0x50)
+        );
+        let endpoints = &storage.endpoints;
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].is_in());
+        assert_eq!(endpoints[0].number(), 1);
+        assert_eq!(endpoints[0].transfer_type(), TransferType::Bulk);
+        assert_eq!(endpoints[0].max_packet_size, 512);
+        assert_eq!(endpoints[0].dci(), 3);
+        assert!(!endpoints[1].is_in());
+        assert_eq!(endpoints[1].dci(), 4);
+
+        // The class-specific descriptor and the alternate setting are skipped.
+        let hid = &config.interfaces[1];
+        assert_eq!(hid.number, 1);
+        assert_eq!(hid.endpoints.len(), 1);
+        let endpoint = hid.endpoints[0];
+        assert_eq!(endpoint.transfer_type(), TransferType::Interrupt);
+        assert_eq!(endpoint.max_packet_size, 8);
+        assert_eq!(endpoint.interval, 10);
+        assert_eq!(endpoint.dci(), 7);
+    }
+
+    #[ktest]
+    fn parse_truncated_config() {
+        // The total length limits the descriptors that are parsed.
+        let mut config = CONFIG;
+        config[2] = 9 + 9 + 7;
+        let parsed = ConfigDescriptor::parse(&config).unwrap();
+        assert_eq!(parsed.interfaces.len(), 1);
+        assert_eq!(parsed.interfaces[0].endpoints.len(), 1);
+
+        // A descriptor must not be shorter than its header or longer than the rest.
+        let mut config = CONFIG;
+        config[9 + 9] = 1;
+        assert_eq!(
+            ConfigDescriptor::parse(&config).unwrap_err(),
+            UsbError::InvalidResponse
+        );
+        let mut config = CONFIG;
+        config[9 + 9] = 60;
+        assert_eq!(
+            ConfigDescriptor::parse(&config).unwrap_err(),
+            UsbError::InvalidResponse
+        );
+    }
+}
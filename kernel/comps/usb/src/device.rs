@@ -0,0 +1,618 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The USB devices and their transfers.
+//!
+//! A device is addressed in two steps: the default control endpoint is set up with the maximum
+//! packet size that is derived from the speed, and it is updated after the first eight bytes of
+//! the device descriptor are read. The configuration is then selected and the class drivers set
+//! up the endpoints of their interfaces.
+//!
+//! The control transfers and the bulk transfers are synchronous, and they are serialized for each
+//! endpoint. The interrupt transfers are asynchronous, and their completions are delivered to the
+//! handlers of the endpoints.
+//!
+//! Reference: Universal Serial Bus Specification, Revision 2.0, Section 9.1 (USB Device States).
+
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use core::{fmt::Debug, time::Duration};
+
+use log::{debug, info};
+use ostd::{
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, PAGE_SIZE},
+    sync::{Mutex, SpinLock},
+};
+
+use crate::{
+    descriptor::{
+        descriptor_type, ConfigDescriptor, DeviceDescriptor, EndpointDescriptor, TransferType,
+        CONFIG_DESCRIPTOR_LEN, DEVICE_DESCRIPTOR_LEN,
+    },
+    xhci::{
+        context::{endpoint_type, EndpointContext, InputContext, SlotContext},
+        ring::{
+            completion_code, trb_type, ProducerRing, Trb, TRB_CH, TRB_DIR_IN, TRB_IDT, TRB_IOC,
+        },
+        TransferHandler, XhciController,
+    },
+    UsbError, UsbSpeed,
+};
+
+/// The time that a control transfer takes at most.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+/// The DCI of the default control endpoint.
+const CONTROL_DCI: u8 = 1;
+/// The maximum number of bytes that a TRB transfers. A TRB must not cross a 64 KiB boundary.
+const MAX_TRB_LEN: usize = 0x10000;
+
+/// The standard requests.
+pub(crate) mod request {
+    pub(crate) const GET_STATUS: u8 = 0;
+    pub(crate) const CLEAR_FEATURE: u8 = 1;
+    pub(crate) const SET_FEATURE: u8 = 3;
+    pub(crate) const GET_DESCRIPTOR: u8 = 6;
+    pub(crate) const SET_CONFIGURATION: u8 = 9;
+}
+
+/// The bits of the `bmRequestType` field.
+pub(crate) mod request_type {
+    pub(crate) const IN: u8 = 0x80;
+    pub(crate) const CLASS: u8 = 0x20;
+    pub(crate) const INTERFACE: u8 = 0x01;
+    pub(crate) const ENDPOINT: u8 = 0x02;
+    pub(crate) const OTHER: u8 = 0x03;
+}
+
+/// The `ENDPOINT_HALT` feature selector.
+const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+/// The location of a device in the USB topology.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PortPath {
+    /// The root port that the device is attached to, either directly or through the hubs.
+    pub(crate) root_port: u8,
+    /// The ports of the hubs below the root port, four bits for each tier.
+    pub(crate) route_string: u32,
+    /// The number of the hubs between the root port and the device.
+    pub(crate) depth: u8,
+    /// The slot ID and the port of the high-speed hub that translates the transactions of the
+    /// low-speed or full-speed device.
+    pub(crate) tt: Option<(u8, u8)>,
+}
+
+/// An enumerated USB device.
+pub struct UsbDevice {
+    controller: Arc<XhciController>,
+    slot_id: u8,
+    speed: UsbSpeed,
+    path: PortPath,
+    descriptor: DeviceDescriptor,
+    config: ConfigDescriptor,
+    /// The slot context, which is updated when the endpoints are configured.
+    slot_context: Mutex<SlotContext>,
+    input_context: Mutex<InputContext>,
+    endpoints: SpinLock<BTreeMap<u8, Arc<Endpoint>>>,
+    /// The buffer of the data stages of the control transfers.
+    control_buf: DmaCoherent,
+}
+
+/// An endpoint with its transfer ring.
+#[derive(Debug)]
+pub(crate) struct Endpoint {
+    dci: u8,
+    /// The endpoint address, which is used to clear the halt feature.
+    address: u8,
+    ring: SpinLock<ProducerRing>,
+    /// The lock that serializes the synchronous transfers.
+    transfer_lock: Mutex<()>,
+}
+
+impl UsbDevice {
+    /// Addresses the device at the port and selects its first configuration.
+    pub(crate) fn new(
+        controller: Arc<XhciController>,
+        speed: UsbSpeed,
+        path: PortPath,
+    ) -> Result<Arc<Self>, UsbError> {
+        let slot_id = controller.enable_slot()?;
+
+        let control_ring = ProducerRing::new()?;
+        let max_packet_size0 = match speed {
+            UsbSpeed::Low | UsbSpeed::Full => 8,
+            UsbSpeed::High => 64,
+            UsbSpeed::Super => 512,
+        };
+        let slot_context = SlotContext {
+            route_string: path.route_string,
+            speed: speed.speed_id(),
+            context_entries: CONTROL_DCI as u32,
+            root_port: path.root_port,
+            tt_hub_slot_id: path.tt.map_or(0, |(slot_id, _)| slot_id),
+            tt_port: path.tt.map_or(0, |(_, port)| port),
+            ..Default::default()
+        };
+
+        let input_context = InputContext::new(controller.context_size())?;
+        input_context.reset();
+        input_context.set_slot(slot_context);
+        input_context.set_endpoint(
+            CONTROL_DCI,
+            EndpointContext::control(max_packet_size0, &control_ring),
+        );
+        controller.address_device(slot_id, &input_context)?;
+
+        let control_endpoint = Arc::new(Endpoint {
+            dci: CONTROL_DCI,
+            address: 0,
+            ring: SpinLock::new(control_ring),
+            transfer_lock: Mutex::new(()),
+        });
+        let control_buf = {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(1)
+                .map_err(|_| UsbError::NoMemory)?;
+            DmaCoherent::map(segment.into(), true).map_err(|_| UsbError::NoMemory)?
+        };
+
+        let mut device = Self {
+            controller,
+            slot_id,
+            speed,
+            path,
+            descriptor: DeviceDescriptor {
+                usb_version: 0,
+                class: 0,
+                subclass: 0,
+                protocol: 0,
+                max_packet_size0: max_packet_size0 as u8,
+                vendor_id: 0,
+                product_id: 0,
+                nr_configs: 0,
+            },
+            config: ConfigDescriptor {
+                value: 0,
+                interfaces: Vec::new(),
+            },
+            slot_context: Mutex::new(slot_context),
+            input_context: Mutex::new(input_context),
+            endpoints: SpinLock::new(BTreeMap::from([(CONTROL_DCI, control_endpoint)])),
+            control_buf,
+        };
+
+        device.read_descriptors()?;
+        device.control_out(
+            0,
+            request::SET_CONFIGURATION,
+            device.config.value as u16,
+            0,
+            &[],
+        )?;
+
+        info!(
+            "[USB]: usb{}: Device {:04x}:{:04x} ({:?} speed) at slot {}",
+            device.controller.index(),
+            device.descriptor.vendor_id,
+            device.descriptor.product_id,
+            speed,
+            slot_id
+        );
+        Ok(Arc::new(device))
+    }
+
+    /// Reads the device descriptor and the first configuration descriptor.
+    fn read_descriptors(&mut self) -> Result<(), UsbError> {
+        let mut buf = [0u8; DEVICE_DESCRIPTOR_LEN];
+
+        // The maximum packet size of the low-speed and full-speed devices is unknown until the
+        // first eight bytes of the device descriptor are read.
+        if matches!(self.speed, UsbSpeed::Low | UsbSpeed::Full) {
+            self.get_descriptor(descriptor_type::DEVICE, 0, &mut buf[..8])?;
+            let max_packet_size0 = buf[7] as u16;
+            if max_packet_size0 != self.descriptor.max_packet_size0 as u16 {
+                self.update_max_packet_size0(max_packet_size0)?;
+            }
+        }
+
+        let len = self.get_descriptor(descriptor_type::DEVICE, 0, &mut buf)?;
+        self.descriptor = DeviceDescriptor::parse(&buf[..len])?;
+        if self.descriptor.nr_configs == 0 {
+            return Err(UsbError::InvalidResponse);
+        }
+
+        let mut header = [0u8; CONFIG_DESCRIPTOR_LEN];
+        self.get_descriptor(descriptor_type::CONFIGURATION, 0, &mut header)?;
+        let total_len = ConfigDescriptor::total_len(&header)?.min(PAGE_SIZE);
+        let mut buf = vec![0u8; total_len];
+        let len = self.get_descriptor(descriptor_type::CONFIGURATION, 0, &mut buf)?;
+        self.config = ConfigDescriptor::parse(&buf[..len])?;
+
+        Ok(())
+    }
+
+    fn update_max_packet_size0(&self, max_packet_size: u16) -> Result<(), UsbError> {
+        debug!(
+            "[USB]: Slot {}: The maximum packet size of the control endpoint is {}",
+            self.slot_id, max_packet_size
+        );
+
+        let input_context = self.input_context.lock();
+        input_context.reset();
+        let endpoint = self.endpoint(CONTROL_DCI).unwrap();
+        let context =
+            EndpointContext::control(max_packet_size, &endpoint.ring.disable_irq().lock());
+        input_context.set_endpoint(CONTROL_DCI, context);
+        self.controller
+            .evaluate_context(self.slot_id, &input_context)
+    }
+
+    /// Returns the slot ID of the device.
+    pub(crate) fn slot_id(&self) -> u8 {
+        self.slot_id
+    }
+
+    pub(crate) fn controller(&self) -> &Arc<XhciController> {
+        &self.controller
+    }
+
+    pub(crate) fn path(&self) -> &PortPath {
+        &self.path
+    }
+
+    pub(crate) fn config(&self) -> &ConfigDescriptor {
+        &self.config
+    }
+
+    /// Returns the speed of the device.
+    pub fn speed(&self) -> UsbSpeed {
+        self.speed
+    }
+
+    /// Returns the device descriptor.
+    pub fn descriptor(&self) -> &DeviceDescriptor {
+        &self.descriptor
+    }
+
+    fn endpoint(&self, dci: u8) -> Option<Arc<Endpoint>> {
+        self.endpoints.disable_irq().lock().get(&dci).cloned()
+    }
+
+    /// Reads the descriptor into `buf` and returns the number of the bytes that are read.
+    pub(crate) fn get_descriptor(
+        &self,
+        type_: u8,
+        index: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, UsbError> {
+        self.control_in(
+            request_type::IN,
+            request::GET_DESCRIPTOR,
+            ((type_ as u16) << 8) | index as u16,
+            0,
+            buf,
+        )
+    }
+
+    /// Performs a control transfer that reads data from the device.
+    ///
+    /// Returns the number of the bytes that are read.
+    pub(crate) fn control_in(
+        &self,
+        req_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+    ) -> Result<usize, UsbError> {
+        let len = self.control_transfer(
+            req_type | request_type::IN,
+            request,
+            value,
+            index,
+            buf.len(),
+            None,
+        )?;
+        self.control_buf.read_bytes(0, &mut buf[..len]).unwrap();
+        Ok(len)
+    }
+
+    /// Performs a control transfer that writes data to the device.
+    pub(crate) fn control_out(
+        &self,
+        req_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+    ) -> Result<(), UsbError> {
+        self.control_transfer(req_type, request, value, index, data.len(), Some(data))
+            .map(|_| ())
+    }
+
+    fn control_transfer(
+        &self,
+        req_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        len: usize,
+        out_data: Option<&[u8]>,
+    ) -> Result<usize, UsbError> {
+        assert!(len <= PAGE_SIZE);
+        let is_in = req_type & request_type::IN != 0;
+
+        let endpoint = self.endpoint(CONTROL_DCI).unwrap();
+        let _guard = endpoint.transfer_lock.lock();
+        if let Some(data) = out_data {
+            self.control_buf.write_bytes(0, data).unwrap();
+        }
+
+        {
+            let mut ring = endpoint.ring.disable_irq().lock();
+
+            let setup = req_type as u64
+                | ((request as u64) << 8)
+                | ((value as u64) << 16)
+                | ((index as u64) << 32)
+                | ((len as u64) << 48);
+            // The Transfer Type field: no data stage (0), OUT data stage (2), or IN data stage
+            // (3).
+            let transfer_type = match (len, is_in) {
+                (0, _) => 0,
+                (_, false) => 2,
+                (_, true) => 3,
+            };
+            ring.push(Trb::new(
+                trb_type::SETUP_STAGE,
+                setup,
+                8,
+                TRB_IDT | (transfer_type << 16),
+            ));
+
+            if len > 0 {
+                let direction = if is_in { TRB_DIR_IN } else { 0 };
+                ring.push(Trb::new(
+                    trb_type::DATA_STAGE,
+                    self.control_buf.daddr() as u64,
+                    len as u32,
+                    direction,
+                ));
+            }
+
+            // The status stage is in the opposite direction of the data stage.
+            let direction = if len > 0 && is_in { 0 } else { TRB_DIR_IN };
+            ring.push(Trb::new(trb_type::STATUS_STAGE, 0, 0, TRB_IOC | direction));
+        }
+        self.controller.ring_doorbell(self.slot_id, CONTROL_DCI);
+
+        let event =
+            self.controller
+                .wait_for_transfer(self.slot_id, CONTROL_DCI, CONTROL_TIMEOUT)?;
+        self.check_transfer(&endpoint, &event)?;
+
+        // Short packets of the data stage are not reported, so the whole buffer is returned. The
+        // callers check the lengths of the descriptors.
+        Ok(len)
+    }
+
+    /// Checks the completion event of a transfer, and recovers the endpoint if it is halted.
+    fn check_transfer(&self, endpoint: &Endpoint, event: &Trb) -> Result<(), UsbError> {
+        match event.completion_code() {
+            completion_code::SUCCESS | completion_code::SHORT_PACKET => Ok(()),
+            code => {
+                debug!(
+                    "[USB]: Slot {}: The transfer of endpoint {} fails with {}",
+                    self.slot_id, endpoint.dci, code
+                );
+
+                let dequeue_pointer = endpoint.ring.disable_irq().lock().dequeue_pointer();
+                self.controller
+                    .reset_endpoint(self.slot_id, endpoint.dci, dequeue_pointer)?;
+                if endpoint.dci != CONTROL_DCI {
+                    self.control_out(
+                        request_type::ENDPOINT,
+                        request::CLEAR_FEATURE,
+                        FEATURE_ENDPOINT_HALT,
+                        endpoint.address as u16,
+                        &[],
+                    )?;
+                }
+
+                if code == completion_code::STALL_ERROR {
+                    Err(UsbError::Stall)
+                } else {
+                    Err(UsbError::TransferFailed(code))
+                }
+            }
+        }
+    }
+
+    /// Configures the endpoints so that they can be used for transfers.
+    pub(crate) fn configure_endpoints(
+        &self,
+        descriptors: &[EndpointDescriptor],
+    ) -> Result<(), UsbError> {
+        let mut slot_context = self.slot_context.lock();
+        let input_context = self.input_context.lock();
+        input_context.reset();
+
+        let mut new_endpoints = Vec::new();
+        for descriptor in descriptors {
+            let type_ = match (descriptor.transfer_type(), descriptor.is_in()) {
+                (TransferType::Bulk, false) => endpoint_type::BULK_OUT,
+                (TransferType::Bulk, true) => endpoint_type::BULK_IN,
+                (TransferType::Interrupt, false) => endpoint_type::INTERRUPT_OUT,
+                (TransferType::Interrupt, true) => endpoint_type::INTERRUPT_IN,
+                _ => return Err(UsbError::NotSupported),
+            };
+            let ring = ProducerRing::new()?;
+            let max_packet_size = match self.speed {
+                // Up to 1024 bytes for the SuperSpeed bulk endpoints.
+                UsbSpeed::Super if descriptor.transfer_type() == TransferType::Bulk => 1024,
+                _ => descriptor.max_packet_size,
+            };
+
+            input_context.set_endpoint(
+                descriptor.dci(),
+                EndpointContext {
+                    type_,
+                    max_packet_size,
+                    interval: self.interval(descriptor),
+                    dequeue_pointer: ring.dequeue_pointer(),
+                    average_trb_len: if type_ == endpoint_type::INTERRUPT_IN {
+                        descriptor.max_packet_size
+                    } else {
+                        PAGE_SIZE as u16
+                    },
+                },
+            );
+            slot_context.context_entries =
+                slot_context.context_entries.max(descriptor.dci() as u32);
+
+            new_endpoints.push(Arc::new(Endpoint {
+                dci: descriptor.dci(),
+                address: descriptor.address,
+                ring: SpinLock::new(ring),
+                transfer_lock: Mutex::new(()),
+            }));
+        }
+        input_context.set_slot(*slot_context);
+
+        self.controller
+            .configure_endpoint(self.slot_id, &input_context)?;
+
+        let mut endpoints = self.endpoints.disable_irq().lock();
+        for endpoint in new_endpoints {
+            endpoints.insert(endpoint.dci, endpoint);
+        }
+        Ok(())
+    }
+
+    /// Marks the device as a hub in the slot context.
+    pub(crate) fn configure_hub(&self, nr_ports: u8) -> Result<(), UsbError> {
+        let mut slot_context = self.slot_context.lock();
+        let input_context = self.input_context.lock();
+        input_context.reset();
+
+        slot_context.is_hub = true;
+        slot_context.nr_ports = nr_ports;
+        input_context.set_slot(*slot_context);
+        self.controller
+            .configure_endpoint(self.slot_id, &input_context)
+    }
+
+    /// Returns the service interval of the periodic endpoint in the unit of 2^interval * 125
+    /// microseconds.
+    fn interval(&self, descriptor: &EndpointDescriptor) -> u8 {
+        if descriptor.transfer_type() != TransferType::Interrupt {
+            return 0;
+        }
+
+        match self.speed {
+            // The interval is 2^(bInterval - 1) microframes.
+            UsbSpeed::High | UsbSpeed::Super => descriptor.interval.clamp(1, 16) - 1,
+            // The interval is bInterval frames, i.e., bInterval * 8 microframes, which is rounded
+            // down to a power of two.
+            UsbSpeed::Low | UsbSpeed::Full => {
+                let microframes = (descriptor.interval.max(1) as u32) * 8;
+                (31 - microframes.leading_zeros()).clamp(3, 10) as u8
+            }
+        }
+    }
+
+    /// Performs a synchronous bulk transfer of the buffer at `daddr`.
+    ///
+    /// Returns the number of the bytes that are transferred.
+    pub(crate) fn bulk_transfer(
+        &self,
+        descriptor: &EndpointDescriptor,
+        daddr: usize,
+        len: usize,
+        timeout: Duration,
+    ) -> Result<usize, UsbError> {
+        let endpoint = self
+            .endpoint(descriptor.dci())
+            .ok_or(UsbError::NotSupported)?;
+        let _guard = endpoint.transfer_lock.lock();
+
+        {
+            let mut ring = endpoint.ring.disable_irq().lock();
+            let trbs = split_into_trbs(daddr, len);
+            let nr_trbs = trbs.len();
+            for (i, (addr, trb_len)) in trbs.into_iter().enumerate() {
+                let flags = if i + 1 == nr_trbs { TRB_IOC } else { TRB_CH };
+                ring.push(Trb::new(
+                    trb_type::NORMAL,
+                    addr as u64,
+                    trb_len as u32,
+                    flags,
+                ));
+            }
+        }
+        self.controller.ring_doorbell(self.slot_id, endpoint.dci);
+
+        let event = self
+            .controller
+            .wait_for_transfer(self.slot_id, endpoint.dci, timeout)?;
+        self.check_transfer(&endpoint, &event)?;
+
+        // Only the residual length of the last TRB is reported, which is accurate if there is
+        // only one TRB or the transfer is not short.
+        Ok(len.saturating_sub(event.residual_len()))
+    }
+
+    /// Submits an asynchronous interrupt transfer of the buffer at `daddr`.
+    ///
+    /// The completion is delivered to the handler of the endpoint.
+    pub(crate) fn submit_interrupt_transfer(
+        &self,
+        descriptor: &EndpointDescriptor,
+        daddr: usize,
+        len: usize,
+    ) -> Result<(), UsbError> {
+        let endpoint = self
+            .endpoint(descriptor.dci())
+            .ok_or(UsbError::NotSupported)?;
+        endpoint.ring.disable_irq().lock().push(Trb::new(
+            trb_type::NORMAL,
+            daddr as u64,
+            len as u32,
+            TRB_IOC,
+        ));
+        self.controller.ring_doorbell(self.slot_id, endpoint.dci);
+        Ok(())
+    }
+
+    /// Sets the handler of the completions of the interrupt transfers of the endpoint.
+    pub(crate) fn set_interrupt_handler(
+        &self,
+        descriptor: &EndpointDescriptor,
+        handler: TransferHandler,
+    ) {
+        self.controller
+            .set_transfer_handler(self.slot_id, descriptor.dci(), handler);
+    }
+}
+
+/// Splits the buffer into the TRBs, none of which crosses a 64 KiB boundary.
+fn split_into_trbs(daddr: usize, len: usize) -> Vec<(usize, usize)> {
+    let mut trbs = Vec::new();
+    let mut offset = 0;
+    while offset < len || trbs.is_empty() {
+        let addr = daddr + offset;
+        let trb_len = (len - offset).min(MAX_TRB_LEN - addr % MAX_TRB_LEN);
+        trbs.push((addr, trb_len));
+        offset += trb_len;
+    }
+    trbs
+}
+
+impl Debug for UsbDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UsbDevice")
+            .field("controller", &self.controller.index())
+            .field("slot_id", &self.slot_id)
+            .field("speed", &self.speed)
+            .field("path", &self.path)
+            .field("descriptor", &self.descriptor)
+            .finish_non_exhaustive()
+    }
+}
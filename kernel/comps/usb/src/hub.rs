@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The enumeration of the root ports and the USB 2 hubs.
+//!
+//! The ports are polled instead of using the status change endpoints of the hubs, since the
+//! devices are only enumerated when the controllers are initialized.
+//!
+//! Reference: Universal Serial Bus Specification, Revision 2.0, Chapter 11 (Hub Specification).
+
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use log::{info, warn};
+
+use crate::{
+    class,
+    descriptor::descriptor_type,
+    device::{request, request_type, PortPath, UsbDevice},
+    wait_for,
+    xhci::XhciController,
+    UsbError, UsbSpeed, DEVICES,
+};
+
+/// The class code of the hubs.
+pub(crate) const HUB_CLASS: u8 = 0x09;
+
+/// The maximum number of the hubs between a root port and a device.
+const MAX_DEPTH: u8 = 5;
+/// The time that a hub port takes to finish the reset.
+const PORT_RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+// The port features.
+const PORT_RESET: u16 = 4;
+const PORT_POWER: u16 = 8;
+const C_PORT_CONNECTION: u16 = 16;
+const C_PORT_RESET: u16 = 20;
+
+// The bits of the port status.
+const PORT_STAT_CONNECTION: u16 = 1 << 0;
+const PORT_STAT_ENABLE: u16 = 1 << 1;
+const PORT_STAT_LOW_SPEED: u16 = 1 << 9;
+const PORT_STAT_HIGH_SPEED: u16 = 1 << 10;
+
+// The bits of the port status change.
+const PORT_STAT_C_RESET: u16 = 1 << 4;
+
+/// Enumerates the devices on the root ports of the controller.
+pub(crate) fn enumerate_root_ports(controller: &Arc<XhciController>) {
+    for port in 1..=controller.nr_ports() {
+        let speed = match controller.reset_port(port) {
+            Ok(Some(speed)) => speed,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!(
+                    "[USB]: usb{}: Failed to reset port {}: {:?}",
+                    controller.index(),
+                    port,
+                    err
+                );
+                continue;
+            }
+        };
+
+        let path = PortPath {
+            root_port: port,
+            route_string: 0,
+            depth: 0,
+            tt: None,
+        };
+        attach_device(controller, speed, path);
+    }
+}
+
+/// Addresses the device and binds the drivers to it.
+fn attach_device(controller: &Arc<XhciController>, speed: UsbSpeed, path: PortPath) {
+    let device = match UsbDevice::new(controller.clone(), speed, path) {
+        Ok(device) => device,
+        Err(err) => {
+            warn!(
+                "[USB]: usb{}: Failed to enumerate the device at port {} (route {:#x}): {:?}",
+                controller.index(),
+                path.root_port,
+                path.route_string,
+                err
+            );
+            return;
+        }
+    };
+    DEVICES.lock().push(device.clone());
+
+    if device.descriptor().class == HUB_CLASS {
+        if let Err(err) = enumerate_hub(&device) {
+            warn!(
+                "[USB]: Slot {}: Failed to enumerate the hub: {:?}",
+                device.slot_id(),
+                err
+            );
+        }
+        return;
+    }
+
+    class::probe(&device);
+}
+
+/// Powers and resets the ports of the hub, and enumerates the devices on them.
+fn enumerate_hub(hub: &Arc<UsbDevice>) -> Result<(), UsbError> {
+    if hub.speed() == UsbSpeed::Super {
+        info!(
+            "[USB]: Slot {}: USB 3 hubs are not supported",
+            hub.slot_id()
+        );
+        return Err(UsbError::NotSupported);
+    }
+    if hub.path().depth >= MAX_DEPTH {
+        return Err(UsbError::NotSupported);
+    }
+
+    let mut desc = [0u8; 7];
+    hub.control_in(
+        request_type::CLASS,
+        request::GET_DESCRIPTOR,
+        (descriptor_type::HUB as u16) << 8,
+        0,
+        &mut desc,
+    )?;
+    if desc[1] != descriptor_type::HUB {
+        return Err(UsbError::InvalidResponse);
+    }
+    let nr_ports = desc[2];
+    let power_on_delay = Duration::from_millis(desc[5] as u64 * 2);
+    hub.configure_hub(nr_ports)?;
+
+    for port in 1..=nr_ports as u16 {
+        set_port_feature(hub, port, PORT_POWER)?;
+    }
+    crate::delay(power_on_delay.max(Duration::from_millis(100)));
+
+    // The route string has four bits for each tier.
+    for port in 1..=nr_ports.min(15) as u16 {
+        match reset_hub_port(hub, port) {
+            Ok(Some(speed)) => {
+                let parent = hub.path();
+                let tt = match (hub.speed(), speed) {
+                    (UsbSpeed::High, UsbSpeed::Low | UsbSpeed::Full) => {
+                        Some((hub.slot_id(), port as u8))
+                    }
+                    _ => parent.tt,
+                };
+                let path = PortPath {
+                    root_port: parent.root_port,
+                    route_string: parent.route_string | ((port as u32) << (parent.depth * 4)),
+                    depth: parent.depth + 1,
+                    tt,
+                };
+                attach_device(hub.controller(), speed, path);
+            }
+            Ok(None) => (),
+            Err(err) => warn!(
+                "[USB]: Slot {}: Failed to reset hub port {}: {:?}",
+                hub.slot_id(),
+                port,
+                err
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Resets the hub port and returns the speed of the connected device.
+///
+/// Returns `None` if no device is connected to the port.
+fn reset_hub_port(hub: &UsbDevice, port: u16) -> Result<Option<UsbSpeed>, UsbError> {
+    let (status, _) = get_port_status(hub, port)?;
+    if status & PORT_STAT_CONNECTION == 0 {
+        return Ok(None);
+    }
+    clear_port_feature(hub, port, C_PORT_CONNECTION)?;
+
+    set_port_feature(hub, port, PORT_RESET)?;
+    let status = wait_for(PORT_RESET_TIMEOUT, || match get_port_status(hub, port) {
+        Ok((status, change)) if change & PORT_STAT_C_RESET != 0 => Some(Ok(status)),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    })??;
+    clear_port_feature(hub, port, C_PORT_RESET)?;
+    // The reset recovery time.
+    crate::delay(Duration::from_millis(10));
+
+    if status & PORT_STAT_ENABLE == 0 {
+        return Ok(None);
+    }
+    let speed = if status & PORT_STAT_LOW_SPEED != 0 {
+        UsbSpeed::Low
+    } else if status & PORT_STAT_HIGH_SPEED != 0 {
+        UsbSpeed::High
+    } else {
+        UsbSpeed::Full
+    };
+    Ok(Some(speed))
+}
+
+/// Returns the status and the status change of the hub port.
+fn get_port_status(hub: &UsbDevice, port: u16) -> Result<(u16, u16), UsbError> {
+    let mut buf = [0u8; 4];
+    hub.control_in(
+        request_type::CLASS | request_type::OTHER,
+        request::GET_STATUS,
+        0,
+        port,
+        &mut buf,
+    )?;
+    Ok((
+        u16::from_le_bytes([buf[0], buf[1]]),
+        u16::from_le_bytes([buf[2], buf[3]]),
+    ))
+}
+
+fn set_port_feature(hub: &UsbDevice, port: u16, feature: u16) -> Result<(), UsbError> {
+    hub.control_out(
+        request_type::CLASS | request_type::OTHER,
+        request::SET_FEATURE,
+        feature,
+        port,
+        &[],
+    )
+}
+
+fn clear_port_feature(hub: &UsbDevice, port: u16, feature: u16) -> Result<(), UsbError> {
+    hub.control_out(
+        request_type::CLASS | request_type::OTHER,
+        request::CLEAR_FEATURE,
+        feature,
+        port,
+        &[],
+    )
+}
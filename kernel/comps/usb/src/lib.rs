@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The USB stack of Asterinas.
+//!
+//! The stack consists of the xHCI host controller driver, the USB core that enumerates the
+//! devices on the root ports and the hubs, and the class drivers of the HID boot keyboards and
+//! mice and the bulk-only mass storage devices.
+//!
+//! The devices are only enumerated when the controllers are initialized. The devices that are
+//! plugged in later are not supported yet.
+//!
+//! The HID devices are registered to the input layer as `usbhidX`, and the mass storage devices
+//! are registered to the block layer as `sdX`, where `X` is `a`, `b`, and so on.
+//!
+//! Reference: <https://www.intel.com/content/dam/www/public/us/en/documents/technical-specifications/extensible-host-controler-interface-usb-xhci.pdf>
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod class;
+mod descriptor;
+mod device;
+mod hub;
+mod xhci;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{hint::spin_loop, time::Duration};
+
+use component::{init_component, ComponentInitError};
+pub use descriptor::{DeviceDescriptor, EndpointDescriptor, InterfaceDescriptor, TransferType};
+pub use device::UsbDevice;
+use ostd::{sync::SpinLock, timer::Jiffies};
+
+pub use crate::class::storage::UsbStorage;
+
+/// The errors of the USB stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UsbError {
+    /// The controller or the device is not supported by the stack.
+    NotSupported,
+    /// The memory for the rings or the buffers cannot be allocated.
+    NoMemory,
+    /// The controller or the device does not respond in time.
+    Timeout,
+    /// The endpoint is halted by the device.
+    Stall,
+    /// The transfer fails with the completion code.
+    TransferFailed(u8),
+    /// The command of the controller fails with the completion code.
+    CommandFailed(u8),
+    /// The device reports an invalid descriptor or an invalid response.
+    InvalidResponse,
+}
+
+/// The speed of a USB device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsbSpeed {
+    /// 1.5 Mbps.
+    Low,
+    /// 12 Mbps.
+    Full,
+    /// 480 Mbps.
+    High,
+    /// 5 Gbps or higher.
+    Super,
+}
+
+static DEVICES: SpinLock<Vec<Arc<UsbDevice>>> = SpinLock::new(Vec::new());
+
+/// Returns all the enumerated USB devices.
+pub fn all_devices() -> Vec<Arc<UsbDevice>> {
+    DEVICES.lock().clone()
+}
+
+/// Returns all the USB mass storage devices.
+pub fn all_storage_devices() -> Vec<Arc<UsbStorage>> {
+    class::storage::all_devices()
+}
+
+/// Waits until `f` returns `Some`, or fails with [`UsbError::Timeout`] after `timeout`.
+pub(crate) fn wait_for<T>(
+    timeout: Duration,
+    mut f: impl FnMut() -> Option<T>,
+) -> Result<T, UsbError> {
+    let deadline = Jiffies::elapsed().as_duration() + timeout;
+
+    loop {
+        if let Some(value) = f() {
+            return Ok(value);
+        }
+        if Jiffies::elapsed().as_duration() >= deadline {
+            return Err(UsbError::Timeout);
+        }
+        spin_loop();
+    }
+}
+
+/// Busy-waits for `duration`, which is required by the timings of the USB specification.
+pub(crate) fn delay(duration: Duration) {
+    let _ = wait_for::<()>(duration, || None);
+}
+
+#[init_component]
+fn usb_init() -> Result<(), ComponentInitError> {
+    aster_block::bio::bio_segment_pool_init();
+    xhci::init();
+    Ok(())
+}
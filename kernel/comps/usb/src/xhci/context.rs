@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device contexts and the input contexts of xHCI.
+//!
+//! A device context consists of a slot context and 31 endpoint contexts, which are indexed by the
+//! Device Context Index (DCI). The DCI of the default control endpoint is 1, and the DCI of the
+//! other endpoints is `endpoint_number * 2 + is_in`. An input context is a device context
+//! prefixed with an input control context, which selects the contexts to be added or dropped.
+//!
+//! Each context is 32 bytes or 64 bytes, depending on the Context Size (CSZ) capability of the
+//! controller. Only the first 32 bytes are used in either case.
+//!
+//! Reference: xHCI Specification, Revision 1.2, Section 6.2 (Device Context Data Structures).
+
+use ostd::mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo};
+
+use super::ring::ProducerRing;
+use crate::{UsbError, UsbSpeed};
+
+/// The types of the endpoints in the endpoint contexts.
+pub(crate) mod endpoint_type {
+    pub(crate) const BULK_OUT: u32 = 2;
+    pub(crate) const INTERRUPT_OUT: u32 = 3;
+    pub(crate) const CONTROL: u32 = 4;
+    pub(crate) const BULK_IN: u32 = 6;
+    pub(crate) const INTERRUPT_IN: u32 = 7;
+}
+
+/// The fields of a slot context.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SlotContext {
+    pub(crate) route_string: u32,
+    pub(crate) speed: u32,
+    /// The index of the last valid endpoint context.
+    pub(crate) context_entries: u32,
+    pub(crate) is_hub: bool,
+    pub(crate) root_port: u8,
+    pub(crate) nr_ports: u8,
+    /// The slot ID of the high-speed hub that translates the transactions of the low-speed or
+    /// full-speed device.
+    pub(crate) tt_hub_slot_id: u8,
+    pub(crate) tt_port: u8,
+}
+
+impl SlotContext {
+    fn to_dwords(self) -> [u32; 4] {
+        [
+            self.route_string
+                | (self.speed << 20)
+                | ((self.is_hub as u32) << 26)
+                | (self.context_entries << 27),
+            ((self.root_port as u32) << 16) | ((self.nr_ports as u32) << 24),
+            self.tt_hub_slot_id as u32 | ((self.tt_port as u32) << 8),
+            0,
+        ]
+    }
+}
+
+/// The fields of an endpoint context.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EndpointContext {
+    pub(crate) type_: u32,
+    pub(crate) max_packet_size: u16,
+    /// The service interval in the unit of 2^interval * 125 microseconds.
+    pub(crate) interval: u8,
+    pub(crate) dequeue_pointer: u64,
+    pub(crate) average_trb_len: u16,
+}
+
+impl EndpointContext {
+    fn to_dwords(self) -> [u32; 5] {
+        // Allow three consecutive errors before the endpoint is halted.
+        let error_count = 3;
+        [
+            (self.interval as u32) << 16,
+            (error_count << 1) | (self.type_ << 3) | ((self.max_packet_size as u32) << 16),
+            self.dequeue_pointer as u32,
+            (self.dequeue_pointer >> 32) as u32,
+            self.average_trb_len as u32,
+        ]
+    }
+
+    /// Returns the context of the default control endpoint.
+    pub(crate) fn control(max_packet_size: u16, ring: &ProducerRing) -> Self {
+        Self {
+            type_: endpoint_type::CONTROL,
+            max_packet_size,
+            interval: 0,
+            dequeue_pointer: ring.dequeue_pointer(),
+            average_trb_len: 8,
+        }
+    }
+}
+
+/// An input context.
+#[derive(Debug)]
+pub(crate) struct InputContext {
+    buf: DmaCoherent,
+    context_size: usize,
+}
+
+impl InputContext {
+    pub(crate) fn new(context_size: usize) -> Result<Self, UsbError> {
+        Ok(Self {
+            buf: alloc_context_page()?,
+            context_size,
+        })
+    }
+
+    pub(crate) fn daddr(&self) -> u64 {
+        self.buf.daddr() as u64
+    }
+
+    /// Clears the input control context and the slot context.
+    ///
+    /// The endpoint contexts are only read by the controller if they are added, so they need
+    /// not be cleared.
+    pub(crate) fn reset(&self) {
+        let zeros = [0u8; 64];
+        self.buf
+            .write_bytes(0, &zeros[..self.context_size])
+            .unwrap();
+        self.buf
+            .write_bytes(self.context_size, &zeros[..self.context_size])
+            .unwrap();
+    }
+
+    /// Sets the Add Context flag of the context (0 for the slot context).
+    pub(crate) fn add(&self, dci: u8) {
+        let flags: u32 = self.buf.read_val(4).unwrap();
+        self.buf.write_val(4, &(flags | (1 << dci))).unwrap();
+    }
+
+    pub(crate) fn set_slot(&self, slot: SlotContext) {
+        self.buf
+            .write_val(self.context_size, &slot.to_dwords())
+            .unwrap();
+        self.add(0);
+    }
+
+    pub(crate) fn set_endpoint(&self, dci: u8, endpoint: EndpointContext) {
+        let offset = self.context_size * (dci as usize + 1);
+        let zeros = [0u8; 64];
+        self.buf
+            .write_bytes(offset, &zeros[..self.context_size])
+            .unwrap();
+        self.buf.write_val(offset, &endpoint.to_dwords()).unwrap();
+        self.add(dci);
+    }
+}
+
+/// Allocates a zeroed page for a device context or an input context, which are at most 2 KiB.
+pub(crate) fn alloc_context_page() -> Result<DmaCoherent, UsbError> {
+    let segment = FrameAllocOptions::new()
+        .alloc_segment(1)
+        .map_err(|_| UsbError::NoMemory)?;
+    DmaCoherent::map(segment.into(), true).map_err(|_| UsbError::NoMemory)
+}
+
+impl UsbSpeed {
+    /// Returns the speed ID in the slot contexts and the PORTSC registers.
+    pub(crate) fn speed_id(self) -> u32 {
+        match self {
+            Self::Full => 1,
+            Self::Low => 2,
+            Self::High => 3,
+            Self::Super => 4,
+        }
+    }
+
+    pub(crate) fn from_speed_id(id: u32) -> Option<Self> {
+        match id {
+            1 => Some(Self::Full),
+            2 => Some(Self::Low),
+            3 => Some(Self::High),
+            4 | 5 => Some(Self::Super),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,495 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the xHCI host controllers.
+//!
+//! The controller is driven synchronously: a command or a transfer is submitted to its ring, and
+//! the submitter polls the event ring until the completion event arrives. The events are also
+//! consumed by the interrupt handler (or the timer if MSI-X is not available), which delivers
+//! the completions of the interrupt transfers to their handlers and keeps the other
+//! completions for their submitters.
+//!
+//! Reference: xHCI Specification, Revision 1.2, Section 4.2 (Host Controller Initialization).
+
+pub(crate) mod context;
+mod pci;
+mod regs;
+pub(crate) mod ring;
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{fmt::Debug, time::Duration};
+
+use log::{debug, info, warn};
+use ostd::{
+    bus::pci::{
+        capability::{msix::CapabilityMsixData, CapabilityData},
+        cfg_space::{Bar, Command},
+        common_device::PciCommonDevice,
+    },
+    mm::{DmaCoherent, HasDaddr, VmIo},
+    sync::{Mutex, SpinLock},
+    trap::IrqLine,
+};
+
+pub(crate) use self::pci::init;
+use self::{
+    context::{alloc_context_page, InputContext},
+    regs::*,
+    ring::{completion_code, trb_type, EventRing, ProducerRing, Trb},
+};
+use crate::{delay, wait_for, UsbError, UsbSpeed};
+
+/// The time that the controller takes to halt, to reset, or to complete a command.
+const CONTROLLER_TIMEOUT: Duration = Duration::from_secs(1);
+/// The time that the root port takes to finish the reset.
+const PORT_RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The handler of the transfer events of an endpoint.
+pub(crate) type TransferHandler = Box<dyn Fn(&Trb) + Send + Sync>;
+
+/// An xHCI host controller.
+pub(crate) struct XhciController {
+    index: usize,
+    regs: Registers,
+    max_slots: u8,
+    nr_ports: u8,
+    context_size: usize,
+    /// The Device Context Base Address Array.
+    dcbaa: DmaCoherent,
+    /// The scratchpad buffer array and the buffers, which are used by the controller.
+    _scratchpad: Vec<DmaCoherent>,
+    /// The output device contexts, which must live until the slots are disabled.
+    device_contexts: SpinLock<BTreeMap<u8, DmaCoherent>>,
+    command_ring: Mutex<ProducerRing>,
+    event_ring: SpinLock<EventRing>,
+    /// The completions of the commands, which are indexed by the addresses of the commands.
+    command_completions: SpinLock<BTreeMap<u64, Trb>>,
+    /// The completions of the transfers, which are indexed by the slot IDs and the DCIs.
+    ///
+    /// At most one transfer is outstanding for each endpoint without a handler.
+    transfer_completions: SpinLock<BTreeMap<(u8, u8), Trb>>,
+    transfer_handlers: SpinLock<BTreeMap<(u8, u8), Arc<TransferHandler>>>,
+    /// The MSI-X capability, which owns the IRQ line.
+    ///
+    /// The event ring is polled by the timer if the controller does not support MSI-X.
+    msix: SpinLock<Option<CapabilityMsixData>>,
+}
+
+impl XhciController {
+    /// Initializes and starts the controller.
+    pub(crate) fn init(index: usize, device: &PciCommonDevice) -> Result<Arc<Self>, UsbError> {
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(0).clone() else {
+            return Err(UsbError::NotSupported);
+        };
+        device.set_command(device.command() | Command::MEMORY_SPACE | Command::BUS_MASTER);
+        let regs = Registers::new(bar.io_mem().clone());
+
+        Self::take_ownership(&regs);
+        Self::reset(&regs)?;
+
+        let hcsparams1 = regs.read_cap(CAP_HCSPARAMS1);
+        let hcsparams2 = regs.read_cap(CAP_HCSPARAMS2);
+        let hccparams1 = regs.read_cap(CAP_HCCPARAMS1);
+        let max_slots = (hcsparams1 & 0xff) as u8;
+        let nr_ports = (hcsparams1 >> 24) as u8;
+        let context_size = if hccparams1 & HCCPARAMS1_CSZ != 0 {
+            64
+        } else {
+            32
+        };
+        if hccparams1 & HCCPARAMS1_AC64 == 0 {
+            warn!(
+                "[xHCI]: usb{}: The controller does not support 64-bit addressing",
+                index
+            );
+        }
+        let nr_scratchpad_bufs = (((hcsparams2 >> 21) & 0x1f) << 5) | ((hcsparams2 >> 27) & 0x1f);
+
+        regs.write_op(OP_CONFIG, max_slots as u32);
+
+        let dcbaa = alloc_context_page()?;
+        let mut scratchpad = Vec::new();
+        if nr_scratchpad_bufs > 0 {
+            let array = alloc_context_page()?;
+            for i in 0..nr_scratchpad_bufs as usize {
+                let buf = alloc_context_page()?;
+                array.write_val(i * 8, &(buf.daddr() as u64)).unwrap();
+                scratchpad.push(buf);
+            }
+            dcbaa.write_val(0, &(array.daddr() as u64)).unwrap();
+            scratchpad.push(array);
+        }
+        regs.write_op64(OP_DCBAAP, dcbaa.daddr() as u64);
+
+        let command_ring = ProducerRing::new()?;
+        regs.write_op64(OP_CRCR, command_ring.daddr() | CRCR_RCS);
+
+        let event_ring = EventRing::new()?;
+        regs.write_ir0(IR_ERSTSZ, 1);
+        regs.write_ir0_64(IR_ERDP, event_ring.dequeue_daddr());
+        regs.write_ir0_64(IR_ERSTBA, event_ring.erst_daddr());
+        // Moderate the interrupts to at most one per 1 ms (in the unit of 250 ns).
+        regs.write_ir0(IR_IMOD, 4000);
+
+        let controller = Arc::new(Self {
+            index,
+            regs,
+            max_slots,
+            nr_ports,
+            context_size,
+            dcbaa,
+            _scratchpad: scratchpad,
+            device_contexts: SpinLock::new(BTreeMap::new()),
+            command_ring: Mutex::new(command_ring),
+            event_ring: SpinLock::new(event_ring),
+            command_completions: SpinLock::new(BTreeMap::new()),
+            transfer_completions: SpinLock::new(BTreeMap::new()),
+            transfer_handlers: SpinLock::new(BTreeMap::new()),
+            msix: SpinLock::new(None),
+        });
+        controller.init_interrupt(device)?;
+
+        let regs = &controller.regs;
+        regs.write_op(OP_USBCMD, regs.read_op(OP_USBCMD) | USBCMD_RS);
+        wait_for(CONTROLLER_TIMEOUT, || {
+            (regs.read_op(OP_USBSTS) & USBSTS_HCH == 0).then_some(())
+        })?;
+
+        for port in 1..=nr_ports {
+            let portsc = regs.read_op(regs.portsc(port));
+            if portsc & PORTSC_PP == 0 {
+                regs.write_op(regs.portsc(port), portsc_neutral(portsc) | PORTSC_PP);
+            }
+        }
+        // Wait for the power to be good and the devices to be connected.
+        delay(Duration::from_millis(100));
+
+        info!(
+            "[xHCI]: usb{}: {} slots, {} ports",
+            index, max_slots, nr_ports
+        );
+        Ok(controller)
+    }
+
+    /// Takes the ownership of the controller from the BIOS.
+    fn take_ownership(regs: &Registers) {
+        let mut offset = ((regs.read_cap(CAP_HCCPARAMS1) >> 16) << 2) as usize;
+        while offset != 0 {
+            let cap = regs.read_cap(offset);
+            if cap & 0xff == EXT_CAP_LEGACY {
+                regs.write_cap(offset, cap | LEGACY_OS_OWNED);
+                if wait_for(CONTROLLER_TIMEOUT, || {
+                    (regs.read_cap(offset) & LEGACY_BIOS_OWNED == 0).then_some(())
+                })
+                .is_err()
+                {
+                    warn!("[xHCI]: The BIOS does not release the controller");
+                    regs.write_cap(offset, regs.read_cap(offset) & !LEGACY_BIOS_OWNED);
+                }
+
+                let ctlsts = regs.read_cap(offset + 4);
+                regs.write_cap(
+                    offset + 4,
+                    (ctlsts & !LEGCTLSTS_SMI_ENABLES) | LEGCTLSTS_SMI_EVENTS,
+                );
+                return;
+            }
+
+            let next = ((cap >> 8) & 0xff) as usize;
+            if next == 0 {
+                return;
+            }
+            offset += next << 2;
+        }
+    }
+
+    fn reset(regs: &Registers) -> Result<(), UsbError> {
+        let command = regs.read_op(OP_USBCMD);
+        if command & USBCMD_RS != 0 {
+            regs.write_op(OP_USBCMD, command & !USBCMD_RS);
+        }
+        wait_for(CONTROLLER_TIMEOUT, || {
+            (regs.read_op(OP_USBSTS) & USBSTS_HCH != 0).then_some(())
+        })?;
+
+        regs.write_op(OP_USBCMD, USBCMD_HCRST);
+        wait_for(CONTROLLER_TIMEOUT, || {
+            let is_ready = regs.read_op(OP_USBCMD) & USBCMD_HCRST == 0
+                && regs.read_op(OP_USBSTS) & USBSTS_CNR == 0;
+            is_ready.then_some(())
+        })
+    }
+
+    fn init_interrupt(self: &Arc<Self>, device: &PciCommonDevice) -> Result<(), UsbError> {
+        let msix = device
+            .capabilities()
+            .iter()
+            .find_map(|cap| match cap.capability_data() {
+                CapabilityData::Msix(msix) => Some(msix.clone()),
+                _ => None,
+            });
+
+        let Some(mut msix) = msix else {
+            let controller = self.clone();
+            ostd::timer::register_callback(move || controller.process_events());
+            return Ok(());
+        };
+
+        let irq = IrqLine::alloc().map_err(|_| UsbError::NoMemory)?;
+        msix.set_interrupt_vector(irq, 0);
+        let controller = self.clone();
+        msix.irq_mut(0).unwrap().on_active(move |_| {
+            let regs = &controller.regs;
+            regs.write_op(OP_USBSTS, USBSTS_EINT);
+            regs.write_ir0(IR_IMAN, IMAN_IP | IMAN_IE);
+            controller.process_events();
+        });
+        *self.msix.lock() = Some(msix);
+
+        self.regs.write_ir0(IR_IMAN, IMAN_IE);
+        let command = self.regs.read_op(OP_USBCMD);
+        self.regs.write_op(OP_USBCMD, command | USBCMD_INTE);
+        Ok(())
+    }
+
+    /// Returns the index of the controller, which is `X` in the name `usbX`.
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of the root ports.
+    pub(crate) fn nr_ports(&self) -> u8 {
+        self.nr_ports
+    }
+
+    /// Returns the size of the contexts in bytes.
+    pub(crate) fn context_size(&self) -> usize {
+        self.context_size
+    }
+
+    /// Consumes the events in the event ring.
+    pub(crate) fn process_events(&self) {
+        let mut handled = Vec::new();
+
+        {
+            let mut event_ring = self.event_ring.disable_irq().lock();
+            let mut has_events = false;
+            while let Some(event) = event_ring.pop() {
+                has_events = true;
+                match event.type_() {
+                    trb_type::TRANSFER_EVENT => {
+                        let key = (event.slot_id(), event.endpoint_id());
+                        let handler = self
+                            .transfer_handlers
+                            .disable_irq()
+                            .lock()
+                            .get(&key)
+                            .cloned();
+                        match handler {
+                            Some(handler) => handled.push((handler, event)),
+                            None => {
+                                self.transfer_completions
+                                    .disable_irq()
+                                    .lock()
+                                    .insert(key, event);
+                            }
+                        }
+                    }
+                    trb_type::COMMAND_COMPLETION_EVENT => {
+                        self.command_completions
+                            .disable_irq()
+                            .lock()
+                            .insert(event.parameter, event);
+                    }
+                    trb_type::PORT_STATUS_CHANGE_EVENT => {
+                        let port = (event.parameter >> 24) as u8;
+                        debug!("[xHCI]: usb{}: Port {} changed", self.index, port);
+                    }
+                    type_ => debug!("[xHCI]: usb{}: Unexpected event {}", self.index, type_),
+                }
+            }
+
+            if has_events {
+                self.regs
+                    .write_ir0_64(IR_ERDP, event_ring.dequeue_daddr() | ERDP_EHB);
+            }
+        }
+
+        // Call the handlers without the lock, since they may submit new transfers.
+        for (handler, event) in handled {
+            handler(&event);
+        }
+    }
+
+    /// Executes the command and returns its completion event.
+    fn execute_command(&self, command: Trb) -> Result<Trb, UsbError> {
+        let mut command_ring = self.command_ring.lock();
+        let daddr = command_ring.push(command);
+        self.regs.ring_doorbell(0, 0);
+
+        let event = wait_for(CONTROLLER_TIMEOUT, || {
+            self.process_events();
+            self.command_completions.disable_irq().lock().remove(&daddr)
+        })?;
+        drop(command_ring);
+
+        match event.completion_code() {
+            completion_code::SUCCESS => Ok(event),
+            code => Err(UsbError::CommandFailed(code)),
+        }
+    }
+
+    /// Enables a device slot and returns its ID.
+    pub(crate) fn enable_slot(&self) -> Result<u8, UsbError> {
+        let event = self.execute_command(Trb::new(trb_type::ENABLE_SLOT, 0, 0, 0))?;
+        let slot_id = event.slot_id();
+        if slot_id == 0 || slot_id > self.max_slots {
+            return Err(UsbError::InvalidResponse);
+        }
+
+        let device_context = alloc_context_page()?;
+        self.dcbaa
+            .write_val(slot_id as usize * 8, &(device_context.daddr() as u64))
+            .unwrap();
+        self.device_contexts.lock().insert(slot_id, device_context);
+        Ok(slot_id)
+    }
+
+    /// Assigns an address to the device with the input context.
+    pub(crate) fn address_device(&self, slot_id: u8, input: &InputContext) -> Result<(), UsbError> {
+        let control = (slot_id as u32) << 24;
+        self.execute_command(Trb::new(
+            trb_type::ADDRESS_DEVICE,
+            input.daddr(),
+            0,
+            control,
+        ))
+        .map(|_| ())
+    }
+
+    /// Configures the endpoints of the device with the input context.
+    pub(crate) fn configure_endpoint(
+        &self,
+        slot_id: u8,
+        input: &InputContext,
+    ) -> Result<(), UsbError> {
+        let control = (slot_id as u32) << 24;
+        self.execute_command(Trb::new(
+            trb_type::CONFIGURE_ENDPOINT,
+            input.daddr(),
+            0,
+            control,
+        ))
+        .map(|_| ())
+    }
+
+    /// Updates the contexts of the device (e.g., the maximum packet size of the default control
+    /// endpoint) with the input context.
+    pub(crate) fn evaluate_context(
+        &self,
+        slot_id: u8,
+        input: &InputContext,
+    ) -> Result<(), UsbError> {
+        let control = (slot_id as u32) << 24;
+        self.execute_command(Trb::new(
+            trb_type::EVALUATE_CONTEXT,
+            input.daddr(),
+            0,
+            control,
+        ))
+        .map(|_| ())
+    }
+
+    /// Recovers the halted endpoint, whose next transfer starts at `dequeue_pointer`.
+    pub(crate) fn reset_endpoint(
+        &self,
+        slot_id: u8,
+        dci: u8,
+        dequeue_pointer: u64,
+    ) -> Result<(), UsbError> {
+        let control = ((slot_id as u32) << 24) | ((dci as u32) << 16);
+        self.execute_command(Trb::new(trb_type::RESET_ENDPOINT, 0, 0, control))?;
+        self.execute_command(Trb::new(
+            trb_type::SET_TR_DEQUEUE_POINTER,
+            dequeue_pointer,
+            0,
+            control,
+        ))
+        .map(|_| ())
+    }
+
+    /// Notifies the controller of the new transfers of the endpoint.
+    pub(crate) fn ring_doorbell(&self, slot_id: u8, dci: u8) {
+        self.regs.ring_doorbell(slot_id, dci);
+    }
+
+    /// Waits for the completion event of the transfer of the endpoint.
+    pub(crate) fn wait_for_transfer(
+        &self,
+        slot_id: u8,
+        dci: u8,
+        timeout: Duration,
+    ) -> Result<Trb, UsbError> {
+        wait_for(timeout, || {
+            self.process_events();
+            self.transfer_completions
+                .disable_irq()
+                .lock()
+                .remove(&(slot_id, dci))
+        })
+    }
+
+    /// Sets the handler of the transfer events of the endpoint.
+    pub(crate) fn set_transfer_handler(&self, slot_id: u8, dci: u8, handler: TransferHandler) {
+        self.transfer_handlers
+            .disable_irq()
+            .lock()
+            .insert((slot_id, dci), Arc::new(handler));
+    }
+
+    /// Resets the root port and returns the speed of the connected device.
+    ///
+    /// Returns `None` if no device is connected to the port.
+    pub(crate) fn reset_port(&self, port: u8) -> Result<Option<UsbSpeed>, UsbError> {
+        let regs = &self.regs;
+        let offset = regs.portsc(port);
+
+        let portsc = regs.read_op(offset);
+        if portsc & PORTSC_CCS == 0 {
+            return Ok(None);
+        }
+        // Clear the connect status change.
+        regs.write_op(offset, portsc_neutral(portsc) | PORTSC_CSC);
+
+        // The USB 3 ports are enabled after the link training, while the USB 2 ports are
+        // enabled after the reset.
+        if portsc & PORTSC_PED == 0 {
+            regs.write_op(offset, portsc_neutral(portsc) | PORTSC_PR);
+            wait_for(PORT_RESET_TIMEOUT, || {
+                (regs.read_op(offset) & PORTSC_PRC != 0).then_some(())
+            })?;
+            let portsc = regs.read_op(offset);
+            regs.write_op(offset, portsc_neutral(portsc) | PORTSC_PRC);
+            // The reset recovery time.
+            delay(Duration::from_millis(10));
+        }
+
+        let portsc = regs.read_op(offset);
+        if portsc & PORTSC_PED == 0 {
+            return Ok(None);
+        }
+        let speed = UsbSpeed::from_speed_id((portsc >> PORTSC_SPEED_SHIFT) & PORTSC_SPEED_MASK)
+            .ok_or(UsbError::NotSupported)?;
+        Ok(Some(speed))
+    }
+}
+
+impl Debug for XhciController {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("XhciController")
+            .field("index", &self.index)
+            .field("max_slots", &self.max_slots)
+            .field("nr_ports", &self.nr_ports)
+            .field("context_size", &self.context_size)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI driver of the xHCI host controllers.
+//!
+//! The controllers are only claimed when the PCI bus is probed, and they are initialized after
+//! that, since the initialization waits for the controllers and enumerates the devices.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use log::warn;
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    sync::SpinLock,
+};
+
+use super::XhciController;
+
+pub(crate) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(XhciPciDriver));
+
+    let devices = core::mem::take(&mut *CLAIMED_DEVICES.lock());
+    for (index, device) in devices.into_iter().enumerate() {
+        match XhciController::init(index, &device) {
+            Ok(controller) => crate::hub::enumerate_root_ports(&controller),
+            Err(err) => warn!("[xHCI]: Failed to initialize usb{}: {:?}", index, err),
+        }
+    }
+}
+
+const SERIAL_BUS_CLASS: u8 = 0x0C;
+const USB_SUBCLASS: u8 = 0x03;
+const PROG_IF_XHCI: u8 = 0x30;
+
+/// The controllers that are claimed but not initialized.
+static CLAIMED_DEVICES: SpinLock<Vec<PciCommonDevice>> = SpinLock::new(Vec::new());
+
+#[derive(Debug)]
+struct XhciPciDriver;
+
+impl PciDriver for XhciPciDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.class != SERIAL_BUS_CLASS
+            || device_id.subclass != USB_SUBCLASS
+            || device_id.prog_if != PROG_IF_XHCI
+        {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        CLAIMED_DEVICES.lock().push(device);
+        Ok(Arc::new(XhciPciDevice { device_id }))
+    }
+}
+
+#[derive(Debug)]
+struct XhciPciDevice {
+    device_id: PciDeviceId,
+}
+
+impl PciDevice for XhciPciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registers of xHCI.
+//!
+//! Reference: eXtensible Host Controller Interface for Universal Serial Bus (xHCI), Revision 1.2,
+//! Chapter 5 (Register Interface).
+
+use ostd::io::IoMem;
+
+// Capability registers.
+pub(super) const CAP_CAPLENGTH: usize = 0x00;
+pub(super) const CAP_HCSPARAMS1: usize = 0x04;
+pub(super) const CAP_HCSPARAMS2: usize = 0x08;
+pub(super) const CAP_HCCPARAMS1: usize = 0x10;
+pub(super) const CAP_DBOFF: usize = 0x14;
+pub(super) const CAP_RTSOFF: usize = 0x18;
+
+/// 64-bit Addressing Capability.
+pub(super) const HCCPARAMS1_AC64: u32 = 1 << 0;
+/// Context Size, which indicates the 64-byte contexts.
+pub(super) const HCCPARAMS1_CSZ: u32 = 1 << 2;
+
+// Operational registers, relative to the end of the capability registers.
+pub(super) const OP_USBCMD: usize = 0x00;
+pub(super) const OP_USBSTS: usize = 0x04;
+pub(super) const OP_CRCR: usize = 0x18;
+pub(super) const OP_DCBAAP: usize = 0x30;
+pub(super) const OP_CONFIG: usize = 0x38;
+pub(super) const OP_PORTSC_BASE: usize = 0x400;
+pub(super) const OP_PORT_STRIDE: usize = 0x10;
+
+pub(super) const USBCMD_RS: u32 = 1 << 0;
+pub(super) const USBCMD_HCRST: u32 = 1 << 1;
+pub(super) const USBCMD_INTE: u32 = 1 << 2;
+
+pub(super) const USBSTS_HCH: u32 = 1 << 0;
+pub(super) const USBSTS_EINT: u32 = 1 << 3;
+pub(super) const USBSTS_CNR: u32 = 1 << 11;
+
+/// Ring Cycle State.
+pub(super) const CRCR_RCS: u64 = 1 << 0;
+
+/// Current Connect Status.
+pub(super) const PORTSC_CCS: u32 = 1 << 0;
+/// Port Enabled, which is cleared (i.e., the port is disabled) by writing one.
+pub(super) const PORTSC_PED: u32 = 1 << 1;
+/// Port Reset.
+pub(super) const PORTSC_PR: u32 = 1 << 4;
+/// Port Power.
+pub(super) const PORTSC_PP: u32 = 1 << 9;
+pub(super) const PORTSC_SPEED_SHIFT: u32 = 10;
+pub(super) const PORTSC_SPEED_MASK: u32 = 0xf;
+/// Port Link State Write Strobe.
+pub(super) const PORTSC_LWS: u32 = 1 << 16;
+/// Connect Status Change.
+pub(super) const PORTSC_CSC: u32 = 1 << 17;
+/// Port Reset Change.
+pub(super) const PORTSC_PRC: u32 = 1 << 21;
+/// The change bits, which are cleared by writing ones.
+pub(super) const PORTSC_CHANGES: u32 = 0x7f << 17;
+
+// Interrupter registers, relative to the runtime registers.
+pub(super) const RT_IR0: usize = 0x20;
+pub(super) const IR_IMAN: usize = 0x00;
+pub(super) const IR_IMOD: usize = 0x04;
+pub(super) const IR_ERSTSZ: usize = 0x08;
+pub(super) const IR_ERSTBA: usize = 0x10;
+pub(super) const IR_ERDP: usize = 0x18;
+
+/// Interrupt Pending, which is cleared by writing one.
+pub(super) const IMAN_IP: u32 = 1 << 0;
+/// Interrupt Enable.
+pub(super) const IMAN_IE: u32 = 1 << 1;
+
+/// Event Handler Busy, which is cleared by writing one.
+pub(super) const ERDP_EHB: u64 = 1 << 3;
+
+// Extended capabilities.
+pub(super) const EXT_CAP_LEGACY: u32 = 1;
+/// HC BIOS Owned Semaphore.
+pub(super) const LEGACY_BIOS_OWNED: u32 = 1 << 16;
+/// HC OS Owned Semaphore.
+pub(super) const LEGACY_OS_OWNED: u32 = 1 << 24;
+/// The SMI enable bits in the USB Legacy Support Control/Status register.
+pub(super) const LEGCTLSTS_SMI_ENABLES: u32 = (1 << 0) | (1 << 4) | (0x7 << 13);
+/// The SMI event bits in the USB Legacy Support Control/Status register, which are cleared by
+/// writing ones.
+pub(super) const LEGCTLSTS_SMI_EVENTS: u32 = 0x7 << 29;
+
+/// The register blocks of a host controller.
+#[derive(Debug)]
+pub(super) struct Registers {
+    io_mem: IoMem,
+    op_base: usize,
+    rt_base: usize,
+    db_base: usize,
+}
+
+impl Registers {
+    pub(super) fn new(io_mem: IoMem) -> Self {
+        let cap_length: u32 = io_mem.read_once(CAP_CAPLENGTH).unwrap();
+        let op_base = (cap_length & 0xff) as usize;
+        let rt_base = (io_mem.read_once::<u32>(CAP_RTSOFF).unwrap() & !0x1f) as usize;
+        let db_base = (io_mem.read_once::<u32>(CAP_DBOFF).unwrap() & !0x3) as usize;
+
+        Self {
+            io_mem,
+            op_base,
+            rt_base,
+            db_base,
+        }
+    }
+
+    pub(super) fn read_cap(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    pub(super) fn write_cap(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    pub(super) fn read_op(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(self.op_base + offset).unwrap()
+    }
+
+    pub(super) fn write_op(&self, offset: usize, value: u32) {
+        self.io_mem
+            .write_once(self.op_base + offset, &value)
+            .unwrap();
+    }
+
+    pub(super) fn write_op64(&self, offset: usize, value: u64) {
+        write_u64(&self.io_mem, self.op_base + offset, value);
+    }
+
+    /// Returns the offset of the PORTSC register of the port, whose number starts from one.
+    pub(super) fn portsc(&self, port: u8) -> usize {
+        OP_PORTSC_BASE + (port as usize - 1) * OP_PORT_STRIDE
+    }
+
+    pub(super) fn read_ir0(&self, offset: usize) -> u32 {
+        self.io_mem
+            .read_once(self.rt_base + RT_IR0 + offset)
+            .unwrap()
+    }
+
+    pub(super) fn write_ir0(&self, offset: usize, value: u32) {
+        self.io_mem
+            .write_once(self.rt_base + RT_IR0 + offset, &value)
+            .unwrap();
+    }
+
+    pub(super) fn write_ir0_64(&self, offset: usize, value: u64) {
+        write_u64(&self.io_mem, self.rt_base + RT_IR0 + offset, value);
+    }
+
+    /// Rings the doorbell of the slot, or the doorbell of the host controller if `slot_id` is 0.
+    pub(super) fn ring_doorbell(&self, slot_id: u8, target: u8) {
+        self.io_mem
+            .write_once(self.db_base + slot_id as usize * 4, &(target as u32))
+            .unwrap();
+    }
+}
+
+/// Writes a 64-bit register as two 32-bit halves, since the controllers may not support 64-bit
+/// accesses.
+fn write_u64(io_mem: &IoMem, offset: usize, value: u64) {
+    io_mem.write_once(offset, &(value as u32)).unwrap();
+    io_mem
+        .write_once(offset + 4, &((value >> 32) as u32))
+        .unwrap();
+}
+
+/// Returns the PORTSC value that does not change the state of the port when it is written.
+///
+/// The Port Enabled bit and the change bits are cleared by writing ones, so they are masked.
+pub(super) fn portsc_neutral(portsc: u32) -> u32 {
+    portsc & !(PORTSC_PED | PORTSC_PR | PORTSC_LWS | PORTSC_CHANGES)
+}
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The rings of xHCI.
+//!
+//! The command ring and the transfer rings are produced by the host and consumed by the
+//! controller, while the event ring is produced by the controller and consumed by the host. The
+//! ownership of each Transfer Request Block (TRB) is indicated by its cycle bit, which is
+//! compared with the cycle state of the consumer. The cycle state is toggled each time the
+//! consumer wraps around the ring.
+//!
+//! Each ring consists of one page. The last TRB of a producer ring is a link TRB that points back
+//! to the first TRB.
+//!
+//! Reference: xHCI Specification, Revision 1.2, Section 4.9 (TRB Ring).
+
+use core::{
+    mem::size_of,
+    sync::atomic::{fence, Ordering},
+};
+
+use ostd::{
+    mm::{DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+    Pod,
+};
+
+use crate::UsbError;
+
+/// The number of the TRBs in a ring.
+pub(crate) const RING_SIZE: usize = PAGE_SIZE / size_of::<Trb>();
+
+pub(crate) const TRB_CYCLE: u32 = 1 << 0;
+/// Toggle Cycle (link TRBs) or Evaluate Next TRB (other TRBs).
+pub(crate) const TRB_TC: u32 = 1 << 1;
+/// Chain bit.
+pub(crate) const TRB_CH: u32 = 1 << 4;
+/// Interrupt On Completion.
+pub(crate) const TRB_IOC: u32 = 1 << 5;
+/// Immediate Data.
+pub(crate) const TRB_IDT: u32 = 1 << 6;
+/// The direction bit of the data stage and status stage TRBs, which indicates IN.
+pub(crate) const TRB_DIR_IN: u32 = 1 << 16;
+
+/// The types of the TRBs.
+pub(crate) mod trb_type {
+    pub(crate) const NORMAL: u32 = 1;
+    pub(crate) const SETUP_STAGE: u32 = 2;
+    pub(crate) const DATA_STAGE: u32 = 3;
+    pub(crate) const STATUS_STAGE: u32 = 4;
+    pub(crate) const LINK: u32 = 6;
+    pub(crate) const ENABLE_SLOT: u32 = 9;
+    pub(crate) const ADDRESS_DEVICE: u32 = 11;
+    pub(crate) const CONFIGURE_ENDPOINT: u32 = 12;
+    pub(crate) const EVALUATE_CONTEXT: u32 = 13;
+    pub(crate) const RESET_ENDPOINT: u32 = 14;
+    pub(crate) const SET_TR_DEQUEUE_POINTER: u32 = 16;
+    pub(crate) const TRANSFER_EVENT: u32 = 32;
+    pub(crate) const COMMAND_COMPLETION_EVENT: u32 = 33;
+    pub(crate) const PORT_STATUS_CHANGE_EVENT: u32 = 34;
+}
+
+/// The completion codes of the events.
+pub(crate) mod completion_code {
+    pub(crate) const SUCCESS: u8 = 1;
+    pub(crate) const STALL_ERROR: u8 = 6;
+    pub(crate) const SHORT_PACKET: u8 = 13;
+}
+
+/// A Transfer Request Block.
+#[derive(Debug, Clone, Copy, Default, Pod)]
+#[repr(C)]
+pub(crate) struct Trb {
+    pub(crate) parameter: u64,
+    pub(crate) status: u32,
+    pub(crate) control: u32,
+}
+
+impl Trb {
+    pub(crate) fn new(type_: u32, parameter: u64, status: u32, control: u32) -> Self {
+        Self {
+            parameter,
+            status,
+            control: control | (type_ << 10),
+        }
+    }
+
+    pub(crate) fn type_(&self) -> u32 {
+        (self.control >> 10) & 0x3f
+    }
+
+    /// Returns the completion code of an event.
+    pub(crate) fn completion_code(&self) -> u8 {
+        (self.status >> 24) as u8
+    }
+
+    /// Returns the number of the bytes that are not transferred, which is reported by a transfer
+    /// event.
+    pub(crate) fn residual_len(&self) -> usize {
+        (self.status & 0xff_ffff) as usize
+    }
+
+    /// Returns the slot ID of a transfer event or a command completion event.
+    pub(crate) fn slot_id(&self) -> u8 {
+        (self.control >> 24) as u8
+    }
+
+    /// Returns the endpoint ID (i.e., the device context index) of a transfer event.
+    pub(crate) fn endpoint_id(&self) -> u8 {
+        ((self.control >> 16) & 0x1f) as u8
+    }
+}
+
+fn alloc_page() -> Result<DmaCoherent, UsbError> {
+    let segment = FrameAllocOptions::new()
+        .alloc_segment(1)
+        .map_err(|_| UsbError::NoMemory)?;
+    DmaCoherent::map(segment.into(), true).map_err(|_| UsbError::NoMemory)
+}
+
+/// A ring that is produced by the host, i.e., the command ring or a transfer ring.
+#[derive(Debug)]
+pub(crate) struct ProducerRing {
+    ring: DmaCoherent,
+    enqueue: usize,
+    cycle: bool,
+}
+
+impl ProducerRing {
+    pub(crate) fn new() -> Result<Self, UsbError> {
+        let ring = alloc_page()?;
+        let link = Trb::new(trb_type::LINK, ring.daddr() as u64, 0, TRB_TC);
+        ring.write_val((RING_SIZE - 1) * size_of::<Trb>(), &link)
+            .unwrap();
+
+        Ok(Self {
+            ring,
+            enqueue: 0,
+            cycle: true,
+        })
+    }
+
+    /// Returns the device address of the ring.
+    pub(crate) fn daddr(&self) -> u64 {
+        self.ring.daddr() as u64
+    }
+
+    /// Returns the device address of the next TRB and the cycle state, which are used to set
+    /// the dequeue pointer of the controller.
+    pub(crate) fn dequeue_pointer(&self) -> u64 {
+        self.trb_daddr(self.enqueue) | self.cycle as u64
+    }
+
+    fn trb_daddr(&self, index: usize) -> u64 {
+        self.daddr() + (index * size_of::<Trb>()) as u64
+    }
+
+    /// Pushes the TRB to the ring and returns its device address.
+    ///
+    /// The cycle bit of the TRB is set by this method. The caller must make sure that the ring
+    /// is not full.
+    pub(crate) fn push(&mut self, trb: Trb) -> u64 {
+        let index = self.enqueue;
+        let offset = index * size_of::<Trb>();
+        let control = (trb.control & !TRB_CYCLE) | self.cycle as u32;
+
+        // Write the cycle bit last, so the controller does not see a partially written TRB.
+        self.ring.write_val(offset, &trb.parameter).unwrap();
+        self.ring.write_val(offset + 8, &trb.status).unwrap();
+        fence(Ordering::SeqCst);
+        self.ring.write_once(offset + 12, &control).unwrap();
+
+        self.enqueue += 1;
+        if self.enqueue == RING_SIZE - 1 {
+            // Hand the link TRB to the controller, and toggle the cycle state.
+            let link_offset = self.enqueue * size_of::<Trb>();
+            let link_control = (trb_type::LINK << 10) | TRB_TC | self.cycle as u32;
+            fence(Ordering::SeqCst);
+            self.ring
+                .write_once(link_offset + 12, &link_control)
+                .unwrap();
+            self.enqueue = 0;
+            self.cycle = !self.cycle;
+        }
+
+        self.trb_daddr(index)
+    }
+}
+
+/// The event ring, which consists of one segment.
+#[derive(Debug)]
+pub(crate) struct EventRing {
+    ring: DmaCoherent,
+    /// The Event Ring Segment Table.
+    erst: DmaCoherent,
+    dequeue: usize,
+    cycle: bool,
+}
+
+impl EventRing {
+    pub(crate) fn new() -> Result<Self, UsbError> {
+        let ring = alloc_page()?;
+        let erst = alloc_page()?;
+        erst.write_val(0, &(ring.daddr() as u64)).unwrap();
+        erst.write_val(8, &(RING_SIZE as u32)).unwrap();
+
+        Ok(Self {
+            ring,
+            erst,
+            dequeue: 0,
+            cycle: true,
+        })
+    }
+
+    /// Returns the device address of the Event Ring Segment Table.
+    pub(crate) fn erst_daddr(&self) -> u64 {
+        self.erst.daddr() as u64
+    }
+
+    /// Returns the device address of the next event, which is reported to the controller after
+    /// the events are consumed.
+    pub(crate) fn dequeue_daddr(&self) -> u64 {
+        (self.ring.daddr() + self.dequeue * size_of::<Trb>()) as u64
+    }
+
+    /// Pops the next event if the controller has produced it.
+    pub(crate) fn pop(&mut self) -> Option<Trb> {
+        let offset = self.dequeue * size_of::<Trb>();
+        let control: u32 = self.ring.read_once(offset + 12).unwrap();
+        if (control & TRB_CYCLE != 0) != self.cycle {
+            return None;
+        }
+        // Read the other fields after the cycle bit.
+        fence(Ordering::SeqCst);
+        let event: Trb = self.ring.read_val(offset).unwrap();
+
+        self.dequeue += 1;
+        if self.dequeue == RING_SIZE {
+            self.dequeue = 0;
+            self.cycle = !self.cycle;
+        }
+        Some(event)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    const TRB_SIZE: usize = size_of::<Trb>();
+
+    #[ktest]
+    fn parse_trb() {
+        // A transfer event of the endpoint 1 IN of the slot 3, which ends with a short packet.
+        let mut bytes = [0u8; TRB_SIZE];
+        bytes[0..8].copy_from_slice(&0x1234_5670u64.to_le_bytes());
+        bytes[8..12]
+            .copy_from_slice(&(((completion_code::SHORT_PACKET as u32) << 24) | 100).to_le_bytes());
+        bytes[12..16].copy_from_slice(
+            &((3 << 24) | (3 << 16) | (trb_type::TRANSFER_EVENT << 10) | TRB_CYCLE).to_le_bytes(),
+        );
+
+        let event = Trb::from_bytes(&bytes);
+        assert_eq!(event.parameter, 0x1234_5670);
+        assert_eq!(event.type_(), trb_type::TRANSFER_EVENT);
+        assert_eq!(event.completion_code(), completion_code::SHORT_PACKET);
+        assert_eq!(event.residual_len(), 100);
+        assert_eq!(event.slot_id(), 3);
+        assert_eq!(event.endpoint_id(), 3);
+
+        let trb = Trb::new(trb_type::DATA_STAGE, 0, 8, TRB_DIR_IN | TRB_IOC);
+        assert_eq!(trb.type_(), trb_type::DATA_STAGE);
+        assert_eq!(
+            trb.control,
+            TRB_DIR_IN | (trb_type::DATA_STAGE << 10) | TRB_IOC
+        );
+    }
+
+    #[ktest]
+    fn producer_ring_wraps() {
+        let mut ring = ProducerRing::new().unwrap();
+        let base = ring.daddr();
+        assert_eq!(ring.dequeue_pointer(), base | 1);
+
+        // The link TRB is not handed to the controller until the ring wraps around.
+        let link_offset = (RING_SIZE - 1) * TRB_SIZE;
+        let link: Trb = ring.ring.read_val(link_offset).unwrap();
+        assert_eq!(link.type_(), trb_type::LINK);
+        assert_eq!(link.parameter, base);
+        assert_eq!(link.control & TRB_CYCLE, 0);
+
+        for i in 0..RING_SIZE - 1 {
+            let daddr = ring.push(Trb::new(trb_type::NORMAL, i as u64, 0, TRB_IOC));
+            assert_eq!(daddr, base + (i * TRB_SIZE) as u64);
+        }
+        for i in 0..RING_SIZE - 1 {
+            let trb: Trb = ring.ring.read_val(i * TRB_SIZE).unwrap();
+            assert_eq!(trb.parameter, i as u64);
+            assert_eq!(trb.type_(), trb_type::NORMAL);
+            assert_eq!(trb.control & (TRB_IOC | TRB_CYCLE), TRB_IOC | TRB_CYCLE);
+        }
+        let link: Trb = ring.ring.read_val(link_offset).unwrap();
+        assert_eq!(link.type_(), trb_type::LINK);
+        assert_eq!(link.control & (TRB_TC | TRB_CYCLE), TRB_TC | TRB_CYCLE);
+
+        // The cycle state is toggled, regardless of the cycle bits of the pushed TRBs.
+        assert_eq!(ring.dequeue_pointer(), base);
+        let daddr = ring.push(Trb::new(trb_type::NORMAL, 0x42, 0, TRB_CYCLE));
+        assert_eq!(daddr, base);
+        let trb: Trb = ring.ring.read_val(0).unwrap();
+        assert_eq!(trb.parameter, 0x42);
+        assert_eq!(trb.control & TRB_CYCLE, 0);
+        assert_eq!(ring.dequeue_pointer(), base + TRB_SIZE as u64);
+    }
+
+    #[ktest]
+    fn event_ring_wraps() {
+        let mut ring = EventRing::new().unwrap();
+        let base = ring.ring.daddr() as u64;
+        assert_eq!(ring.erst.read_val::<u64>(0).unwrap(), base);
+        assert_eq!(ring.erst.read_val::<u32>(8).unwrap(), RING_SIZE as u32);
+
+        let post = |ring: &EventRing, index: usize, cycle: bool, parameter: u64| {
+            let event = Trb::new(
+                trb_type::COMMAND_COMPLETION_EVENT,
+                parameter,
+                (completion_code::SUCCESS as u32) << 24,
+                cycle as u32,
+            );
+            ring.ring.write_val(index * TRB_SIZE, &event).unwrap();
+        };
+
+        // The zeroed TRBs are owned by the controller.
+        assert!(ring.pop().is_none());
+
+        for i in 0..RING_SIZE {
+            post(&ring, i, true, i as u64);
+        }
+        for i in 0..RING_SIZE {
+            let event = ring.pop().unwrap();
+            assert_eq!(event.parameter, i as u64);
+            assert_eq!(event.completion_code(), completion_code::SUCCESS);
+        }
+        assert_eq!(ring.dequeue_daddr(), base);
+
+        // The events of the last pass are consumed, and the cycle state is toggled.
+        assert!(ring.pop().is_none());
+        post(&ring, 0, false, 0x42);
+        assert_eq!(ring.pop().unwrap().parameter, 0x42);
+        assert_eq!(ring.dequeue_daddr(), base + TRB_SIZE as u64);
+        assert!(ring.pop().is_none());
+    }
+}
@@ -8,18 +8,26 @@ use alloc::{
     vec,
     vec::Vec,
 };
-use core::{fmt::Debug, hint::spin_loop, mem::size_of};
+use core::{
+    fmt::Debug,
+    hint::spin_loop,
+    mem::size_of,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use aster_block::{
     bio::{bio_segment_pool_init, BioEnqueueError, BioStatus, BioType, SubmittedBio},
     request_queue::{BioRequest, BioRequestSingleQueue},
+    timeout::{self, HungIoStats, RequestDeadline, TimeoutAction},
     BlockDeviceMeta,
 };
 use id_alloc::IdAlloc;
 use log::{debug, info};
 use ostd::{
-    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo},
-    sync::SpinLock,
+    cpu::{current_cpu_racy, num_cpus},
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::{SpinLock, WaitQueue},
     trap::TrapFrame,
     Pod,
 };
@@ -36,9 +44,11 @@ use crate::{
 
 #[derive(Debug)]
 pub struct BlockDevice {
+    name: String,
     device: Arc<DeviceInner>,
     /// The software staging queue.
     queue: BioRequestSingleQueue,
+    hung_io_stats: HungIoStats,
 }
 
 impl BlockDevice {
@@ -54,12 +64,21 @@ impl BlockDevice {
         };
 
         let block_device = Arc::new(Self {
+            name: device_id.clone(),
             device,
             // Each bio request includes an additional 1 request and 1 response descriptor,
             // therefore this upper bound is set to (QUEUE_SIZE - 2).
             queue: BioRequestSingleQueue::with_max_nr_segments_per_bio(
                 (DeviceInner::QUEUE_SIZE - 2) as usize,
             ),
+            hung_io_stats: HungIoStats::new(),
+        });
+
+        let cloned_device = block_device.clone();
+        timeout::register_timeout_handler(move || {
+            cloned_device
+                .device
+                .handle_timeouts(&cloned_device.name, &cloned_device.hung_io_stats);
         });
 
         aster_block::register_device(device_id, block_device);
@@ -68,23 +87,28 @@ impl BlockDevice {
         Ok(())
     }
 
+    /// Returns the number of the request virtqueues.
+    ///
+    /// Each CPU submits the requests to one of the virtqueues. To make use of all the
+    /// virtqueues, the requests should be handled on this number of different CPUs.
+    pub fn nr_queues(&self) -> usize {
+        self.device.queues.len()
+    }
+
     /// Dequeues a `BioRequest` from the software staging queue and
     /// processes the request.
+    ///
+    /// The request is submitted to the virtqueue of the current CPU.
     pub fn handle_requests(&self) {
         let request = self.queue.dequeue();
         info!("Handle Request: {:?}", request);
-        match request.type_() {
-            BioType::Read => self.device.read(request),
-            BioType::Write => self.device.write(request),
-            BioType::Flush => self.device.flush(request),
-            BioType::Discard => todo!(),
-        }
+        let queue_index = current_cpu_racy().as_usize() % self.nr_queues();
+        self.device.submit(queue_index, request);
     }
 
     /// Negotiate features for the device specified bits 0~23
     pub(crate) fn negotiate_features(features: u64) -> u64 {
-        let mut support_features = BlockFeatures::from_bits_truncate(features);
-        support_features.remove(BlockFeatures::MQ);
+        let support_features = BlockFeatures::from_bits_truncate(features);
         support_features.bits
     }
 }
@@ -100,22 +124,71 @@ impl aster_block::BlockDevice for BlockDevice {
             nr_sectors: self.device.config_manager.capacity_sectors(),
         }
     }
+
+    fn hung_io_stats(&self) -> Option<&HungIoStats> {
+        Some(&self.hung_io_stats)
+    }
+
+    fn request_queue(&self) -> Option<&BioRequestSingleQueue> {
+        Some(&self.queue)
+    }
+
+    fn max_discard_sectors(&self) -> usize {
+        if !self.device.features.support_discard {
+            return 0;
+        }
+        self.device.config_manager.max_discard_sectors() as usize
+    }
+
+    fn max_write_zeroes_sectors(&self) -> usize {
+        if !self.device.features.support_write_zeroes {
+            return 0;
+        }
+        self.device.config_manager.max_write_zeroes_sectors() as usize
+    }
 }
 
-#[derive(Debug)]
 struct DeviceInner {
     config_manager: ConfigManager<VirtioBlockConfig>,
     features: VirtioBlockFeature,
-    queue: SpinLock<VirtQueue>,
+    /// The request virtqueues.
+    queues: Vec<RequestQueue>,
     transport: SpinLock<Box<dyn VirtioTransport>>,
     block_requests: DmaStream,
     block_responses: DmaStream,
+    /// The segments of the discard and write zeroes requests.
+    block_segments: DmaStream,
     id_allocator: SpinLock<IdAlloc>,
+    /// The number of the submitted requests that modify the sectors.
+    nr_inflight_writes: AtomicUsize,
+    /// The wait queue for the submitted requests that modify the sectors to complete.
+    inflight_writes_wait_queue: WaitQueue,
+}
+
+impl Debug for DeviceInner {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("DeviceInner")
+            .field("config_manager", &self.config_manager)
+            .field("features", &self.features)
+            .field("queues", &self.queues)
+            .field("transport", &self.transport)
+            .field("id_allocator", &self.id_allocator)
+            .field("nr_inflight_writes", &self.nr_inflight_writes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request virtqueue and the requests submitted to it.
+#[derive(Debug)]
+struct RequestQueue {
+    queue: SpinLock<VirtQueue>,
     submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>>,
 }
 
 impl DeviceInner {
     const QUEUE_SIZE: u16 = 64;
+    /// The number of times a timed-out request is requeued before it is aborted.
+    const MAX_TIMEOUT_REQUEUES: u8 = 3;
 
     /// Creates and inits the device.
     pub fn init(mut transport: Box<dyn VirtioTransport>) -> Result<Arc<Self>, VirtioDeviceError> {
@@ -126,47 +199,52 @@ impl DeviceInner {
             VirtioBlockConfig::sector_size(),
             "currently not support customized device logical block size"
         );
-        let num_queues = transport.num_queues();
-        if num_queues != 1 {
-            // FIXME: support Multi-Queue Block IO Queueing Mechanism
-            // (`BlkFeatures::MQ`) to accelerate multi-processor requests for
-            // block devices. When SMP is enabled on x86, the feature is on.
-            // We should also consider negotiating the feature in the future.
-            // return Err(VirtioDeviceError::QueuesAmountDoNotMatch(num_queues, 1));
-            log::warn!(
-                "Not supporting Multi-Queue Block IO Queueing Mechanism, only using the first queue"
-            );
-        }
         let features = VirtioBlockFeature::new(transport.as_ref());
-        let queue = VirtQueue::new(0, Self::QUEUE_SIZE, transport.as_mut())
-            .expect("create virtqueue failed");
-        let block_requests = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
-            DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap()
+
+        // With the Multi-Queue Block IO Queueing Mechanism (`BlockFeatures::MQ`), the device
+        // provides multiple request virtqueues. One virtqueue is used per CPU, so the extra
+        // virtqueues are left unused.
+        let device_nr_queues = if features.support_mq {
+            config_manager.num_queues().min(transport.num_queues())
+        } else {
+            1
         };
-        assert!(Self::QUEUE_SIZE as usize * REQ_SIZE <= block_requests.nbytes());
-        let block_responses = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+        let nr_queues = (device_nr_queues as usize).clamp(1, num_cpus());
+        let queues = (0..nr_queues)
+            .map(|index| {
+                let queue = VirtQueue::new(index as u16, Self::QUEUE_SIZE, transport.as_mut())
+                    .expect("create virtqueue failed");
+                RequestQueue {
+                    queue: SpinLock::new(queue),
+                    submitted_requests: SpinLock::new(BTreeMap::new()),
+                }
+            })
+            .collect();
+
+        let nr_ids = Self::QUEUE_SIZE as usize * nr_queues;
+        let alloc_stream = |nbytes: usize| {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(nbytes.div_ceil(PAGE_SIZE))
+                .unwrap();
             DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap()
         };
-        assert!(Self::QUEUE_SIZE as usize * RESP_SIZE <= block_responses.nbytes());
+        let block_requests = alloc_stream(nr_ids * REQ_SIZE);
+        let block_responses = alloc_stream(nr_ids * RESP_SIZE);
+        let block_segments = alloc_stream(nr_ids * SEG_SIZE);
 
         let device = Arc::new(Self {
             config_manager,
             features,
-            queue: SpinLock::new(queue),
+            queues,
             transport: SpinLock::new(transport),
             block_requests,
             block_responses,
-            id_allocator: SpinLock::new(IdAlloc::with_capacity(Self::QUEUE_SIZE as usize)),
-            submitted_requests: SpinLock::new(BTreeMap::new()),
+            block_segments,
+            id_allocator: SpinLock::new(IdAlloc::with_capacity(nr_ids)),
+            nr_inflight_writes: AtomicUsize::new(0),
+            inflight_writes_wait_queue: WaitQueue::new(),
         });
 
-        let cloned_device = device.clone();
-        let handle_irq = move |_: &TrapFrame| {
-            cloned_device.handle_irq();
-        };
-
         let cloned_device = device.clone();
         let handle_config_change = move |_: &TrapFrame| {
             cloned_device.handle_config_change();
@@ -177,29 +255,42 @@ impl DeviceInner {
             transport
                 .register_cfg_callback(Box::new(handle_config_change))
                 .unwrap();
-            transport
-                .register_queue_callback(0, Box::new(handle_irq), false)
-                .unwrap();
+            for index in 0..nr_queues {
+                let cloned_device = device.clone();
+                let handle_irq = move |_: &TrapFrame| {
+                    cloned_device.handle_irq(index);
+                };
+                // Each virtqueue gets its own interrupt if possible, so that the requests
+                // can be completed on different CPUs.
+                transport
+                    .register_queue_callback(index as u16, Box::new(handle_irq), nr_queues > 1)
+                    .unwrap();
+            }
             transport.finish_init();
         }
 
         Ok(device)
     }
 
-    /// Handles the irq issued from the device
-    fn handle_irq(&self) {
+    /// Handles the irq issued from the device for the `queue_index`-th virtqueue.
+    fn handle_irq(&self, queue_index: usize) {
         info!("Virtio block device handle irq");
+        let request_queue = &self.queues[queue_index];
         // When we enter the IRQs handling function,
         // IRQs have already been disabled,
         // so there is no need to call `disable_irq`.
         loop {
             // Pops the complete request
             let complete_request = {
-                let mut queue = self.queue.lock();
+                let mut queue = request_queue.queue.lock();
                 let Ok((token, _)) = queue.pop_used() else {
                     return;
                 };
-                self.submitted_requests.lock().remove(&token).unwrap()
+                request_queue
+                    .submitted_requests
+                    .lock()
+                    .remove(&token)
+                    .unwrap()
             };
 
             // Handles the response
@@ -208,14 +299,24 @@ impl DeviceInner {
             resp_slice.sync().unwrap();
             let resp: BlockResp = resp_slice.read_val(0).unwrap();
             self.id_allocator.lock().free(id);
-            match RespStatus::try_from(resp.status).unwrap() {
-                RespStatus::Ok => {}
-                // FIXME: Return an error instead of triggering a kernel panic
-                _ => panic!("io error in block device"),
+
+            // The bios of an aborted request have already been completed
+            if complete_request.is_aborted {
+                continue;
+            }
+            if complete_request.is_write {
+                self.end_write();
+            }
+            let status = match RespStatus::try_from(resp.status) {
+                Ok(RespStatus::Ok) => BioStatus::Complete,
+                Ok(RespStatus::Unsupported) => BioStatus::NotSupported,
+                _ => BioStatus::IoError,
             };
 
             // Synchronize DMA mapping if read from the device
-            if let BioType::Read = complete_request.bio_request.type_() {
+            if status == BioStatus::Complete
+                && complete_request.bio_request.type_() == BioType::Read
+            {
                 complete_request
                     .bio_request
                     .bios()
@@ -229,16 +330,84 @@ impl DeviceInner {
 
             // Completes the bio request
             complete_request.bio_request.bios().for_each(|bio| {
-                bio.complete(BioStatus::Complete);
+                bio.complete(status);
             });
         }
     }
 
+    /// Handles the submitted requests that have passed their deadlines.
+    ///
+    /// A VirtIO device cannot abort a single request, so a timed-out request
+    /// stays in the virtqueue and gets a new deadline. After being requeued
+    /// `MAX_TIMEOUT_REQUEUES` times, its bios are completed with
+    /// `BioStatus::TimedOut`, and its descriptors are reclaimed once the
+    /// device eventually uses them.
+    fn handle_timeouts(&self, device_name: &str, stats: &HungIoStats) {
+        for (queue_index, request_queue) in self.queues.iter().enumerate() {
+            self.handle_queue_timeouts(queue_index, request_queue, device_name, stats);
+        }
+    }
+
+    fn handle_queue_timeouts(
+        &self,
+        queue_index: usize,
+        request_queue: &RequestQueue,
+        device_name: &str,
+        stats: &HungIoStats,
+    ) {
+        let expired_requests: Vec<(u16, BioType, Duration)> = request_queue
+            .submitted_requests
+            .lock()
+            .iter()
+            .filter(|(_, request)| !request.is_aborted && request.deadline.has_expired())
+            .map(|(token, request)| {
+                (
+                    *token,
+                    request.bio_request.type_(),
+                    request.deadline.elapsed(),
+                )
+            })
+            .collect();
+        if expired_requests.is_empty() {
+            return;
+        }
+
+        // Completes the requests whose interrupts may have been lost.
+        self.handle_irq(queue_index);
+
+        let mut submitted_requests = request_queue.submitted_requests.lock();
+        for (token, type_, elapsed) in expired_requests {
+            // The token may have been reused by a new request after the old one completed.
+            let Some(request) = submitted_requests
+                .get_mut(&token)
+                .filter(|request| request.deadline.has_expired())
+            else {
+                stats.record(device_name, type_, elapsed, TimeoutAction::Recovered);
+                continue;
+            };
+
+            if request.nr_requeues < Self::MAX_TIMEOUT_REQUEUES {
+                request.nr_requeues += 1;
+                request.deadline.restart();
+                stats.record(device_name, type_, elapsed, TimeoutAction::Requeued);
+            } else {
+                request.is_aborted = true;
+                // The aborted request no longer holds back the flushes.
+                if request.is_write {
+                    self.end_write();
+                }
+                request.bio_request.bios().for_each(|bio| {
+                    bio.complete(BioStatus::TimedOut);
+                });
+                stats.record(device_name, type_, elapsed, TimeoutAction::Aborted);
+            }
+        }
+    }
+
     fn handle_config_change(&self) {
         info!("Virtio block device config space change");
     }
 
-    // TODO: Most logic is the same as read and write, there should be a refactor.
     // TODO: Should return an Err instead of panic if the device fails.
     fn request_device_id(&self) -> String {
         let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
@@ -270,7 +439,7 @@ impl DeviceInner {
         let device_id_slice = DmaStreamSlice::new(&device_id_stream, 0, MAX_ID_LENGTH);
         let outputs = vec![&device_id_slice, &resp_slice];
 
-        let mut queue = self.queue.disable_irq().lock();
+        let mut queue = self.queues[0].queue.disable_irq().lock();
         let token = queue
             .add_dma_buf(&[&req_slice], outputs.as_slice())
             .expect("add queue failed");
@@ -304,16 +473,53 @@ impl DeviceInner {
         String::from_utf8(device_id).unwrap()
     }
 
-    /// Reads data from the device, this function is non-blocking.
-    fn read(&self, bio_request: BioRequest) {
+    /// Submits the request to the `queue_index`-th virtqueue, this function is non-blocking
+    /// unless the request is a flush.
+    ///
+    /// A flush only covers the writes that have been completed, so it waits for all the
+    /// submitted writes to complete before being submitted. The flush will be ignored if
+    /// the device doesn't support the `VIRTIO_BLK_F_FLUSH` feature, in which case the
+    /// device has no volatile write cache.
+    fn submit(&self, queue_index: usize, bio_request: BioRequest) {
+        let type_ = bio_request.type_();
+        let req_type = match type_ {
+            BioType::Read => ReqType::In,
+            BioType::Write => ReqType::Out,
+            BioType::Flush if self.features.support_flush => {
+                self.inflight_writes_wait_queue.wait_until(|| {
+                    (self.nr_inflight_writes.load(Ordering::Acquire) == 0).then_some(())
+                });
+                ReqType::Flush
+            }
+            BioType::Discard if self.features.support_discard => ReqType::Discard,
+            BioType::WriteZeroes if self.features.support_write_zeroes => ReqType::WriteZeroes,
+            BioType::Flush => {
+                bio_request.bios().for_each(|bio| {
+                    bio.complete(BioStatus::Complete);
+                });
+                return;
+            }
+            BioType::Discard | BioType::WriteZeroes => {
+                bio_request.bios().for_each(|bio| {
+                    bio.complete(BioStatus::NotSupported);
+                });
+                return;
+            }
+        };
+        // The sector is only used by the read and write requests.
+        let sector = match type_ {
+            BioType::Read | BioType::Write => bio_request.sid_range().start.to_raw(),
+            _ => 0,
+        };
+
         let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
         let req_slice = {
             let req_slice =
                 DmaStreamSlice::new(self.block_requests.clone(), id * REQ_SIZE, REQ_SIZE);
             let req = BlockReq {
-                type_: ReqType::In as _,
+                type_: req_type as _,
                 reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
+                sector,
             };
             req_slice.write_val(0, &req).unwrap();
             req_slice.sync().unwrap();
@@ -327,7 +533,22 @@ impl DeviceInner {
             resp_slice
         };
 
-        let outputs = {
+        let seg_slice = {
+            let seg_slice =
+                DmaStreamSlice::new(self.block_segments.clone(), id * SEG_SIZE, SEG_SIZE);
+            let seg = DiscardWriteZeroesSeg {
+                sector: bio_request.sid_range().start.to_raw(),
+                num_sectors: bio_request.num_sectors() as u32,
+                flags: 0,
+            };
+            seg_slice.write_val(0, &seg).unwrap();
+            seg_slice.sync().unwrap();
+            seg_slice
+        };
+
+        let (inputs, outputs) = {
+            let mut inputs: Vec<&DmaStreamSlice<_>> =
+                Vec::with_capacity(bio_request.num_segments() + 2);
             let mut outputs: Vec<&DmaStreamSlice<_>> =
                 Vec::with_capacity(bio_request.num_segments() + 1);
             let dma_slices_iter = bio_request.bios().flat_map(|bio| {
@@ -335,151 +556,57 @@ impl DeviceInner {
                     .iter()
                     .map(|segment| segment.inner_dma_slice())
             });
-            outputs.extend(dma_slices_iter);
+
+            inputs.push(&req_slice);
+            match type_ {
+                BioType::Read => outputs.extend(dma_slices_iter),
+                BioType::Write => inputs.extend(dma_slices_iter),
+                BioType::Discard | BioType::WriteZeroes => inputs.push(&seg_slice),
+                BioType::Flush => {}
+            }
             outputs.push(&resp_slice);
-            outputs
+            (inputs, outputs)
         };
 
-        let num_used_descs = outputs.len() + 1;
+        let num_used_descs = inputs.len() + outputs.len();
         // FIXME: Split the request if it is too big
         if num_used_descs > Self::QUEUE_SIZE as usize {
             panic!("The request size surpasses the queue size");
         }
 
+        let request_queue = &self.queues[queue_index];
         loop {
-            let mut queue = self.queue.disable_irq().lock();
+            let mut queue = request_queue.queue.disable_irq().lock();
             if num_used_descs > queue.available_desc() {
                 continue;
             }
+
             let token = queue
-                .add_dma_buf(&[&req_slice], outputs.as_slice())
+                .add_dma_buf(inputs.as_slice(), outputs.as_slice())
                 .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
-            }
 
-            // Records the submitted request
+            // Records the submitted request. The IRQ handler cannot pop the request before
+            // the lock of the virtqueue is released.
             let submitted_request = SubmittedRequest::new(id as u16, bio_request);
-            self.submitted_requests
+            if submitted_request.is_write {
+                self.nr_inflight_writes.fetch_add(1, Ordering::Relaxed);
+            }
+            request_queue
+                .submitted_requests
                 .disable_irq()
                 .lock()
                 .insert(token, submitted_request);
-            return;
-        }
-    }
-
-    /// Writes data to the device, this function is non-blocking.
-    fn write(&self, bio_request: BioRequest) {
-        let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
-        let req_slice = {
-            let req_slice =
-                DmaStreamSlice::new(self.block_requests.clone(), id * REQ_SIZE, REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::Out as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
-
-        let resp_slice = {
-            let resp_slice =
-                DmaStreamSlice::new(self.block_responses.clone(), id * RESP_SIZE, RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
-
-        let inputs = {
-            let mut inputs: Vec<&DmaStreamSlice<_>> =
-                Vec::with_capacity(bio_request.num_segments() + 1);
-            inputs.push(&req_slice);
-            let dma_slices_iter = bio_request.bios().flat_map(|bio| {
-                bio.segments()
-                    .iter()
-                    .map(|segment| segment.inner_dma_slice())
-            });
-            inputs.extend(dma_slices_iter);
-            inputs
-        };
-
-        let num_used_descs = inputs.len() + 1;
-        // FIXME: Split the request if it is too big
-        if num_used_descs > Self::QUEUE_SIZE as usize {
-            panic!("The request size surpasses the queue size");
-        }
-        loop {
-            let mut queue = self.queue.disable_irq().lock();
-            if num_used_descs > queue.available_desc() {
-                continue;
-            }
-            let token = queue
-                .add_dma_buf(inputs.as_slice(), &[&resp_slice])
-                .expect("add queue failed");
             if queue.should_notify() {
                 queue.notify();
             }
-
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request);
-            self.submitted_requests
-                .disable_irq()
-                .lock()
-                .insert(token, submitted_request);
             return;
         }
     }
 
-    /// Flushes any cached data from the guest to the persistent storage on the host.
-    /// This will be ignored if the device doesn't support the `VIRTIO_BLK_F_FLUSH` feature.
-    fn flush(&self, bio_request: BioRequest) {
-        if self.features.support_flush {
-            bio_request.bios().for_each(|bio| {
-                bio.complete(BioStatus::Complete);
-            });
-            return;
-        }
-
-        let id = self.id_allocator.disable_irq().lock().alloc().unwrap();
-        let req_slice = {
-            let req_slice = DmaStreamSlice::new(&self.block_requests, id * REQ_SIZE, REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::Flush as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
-
-        let resp_slice = {
-            let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
-
-        let num_used_descs = 1;
-        loop {
-            let mut queue = self.queue.disable_irq().lock();
-            if num_used_descs > queue.available_desc() {
-                continue;
-            }
-            let token = queue
-                .add_dma_buf(&[&req_slice], &[&resp_slice])
-                .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
-            }
-
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request);
-            self.submitted_requests
-                .disable_irq()
-                .lock()
-                .insert(token, submitted_request);
-            return;
+    /// Marks a submitted request that modifies the sectors as no longer in flight.
+    fn end_write(&self) {
+        if self.nr_inflight_writes.fetch_sub(1, Ordering::Release) == 1 {
+            self.inflight_writes_wait_queue.wake_all();
         }
     }
 }
@@ -489,11 +616,28 @@ impl DeviceInner {
 struct SubmittedRequest {
     id: u16,
     bio_request: BioRequest,
+    /// Whether the request modifies the sectors, which must complete before a flush.
+    is_write: bool,
+    deadline: RequestDeadline,
+    nr_requeues: u8,
+    /// Whether the bios have been completed due to a timeout.
+    is_aborted: bool,
 }
 
 impl SubmittedRequest {
     pub fn new(id: u16, bio_request: BioRequest) -> Self {
-        Self { id, bio_request }
+        let is_write = matches!(
+            bio_request.type_(),
+            BioType::Write | BioType::Discard | BioType::WriteZeroes
+        );
+        Self {
+            id,
+            bio_request,
+            is_write,
+            deadline: RequestDeadline::start(),
+            nr_requeues: 0,
+            is_aborted: false,
+        }
     }
 }
 
@@ -508,6 +652,17 @@ struct BlockReq {
 
 const REQ_SIZE: usize = size_of::<BlockReq>();
 
+/// The segment of a VirtIOBlock discard or write zeroes request.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod)]
+struct DiscardWriteZeroesSeg {
+    pub sector: u64,
+    pub num_sectors: u32,
+    pub flags: u32,
+}
+
+const SEG_SIZE: usize = size_of::<DiscardWriteZeroesSeg>();
+
 /// Response of a VirtIOBlock request.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod)]
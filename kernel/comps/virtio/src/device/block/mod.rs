@@ -115,10 +115,13 @@ pub struct VirtioBlockTopology {
     opt_io_size: u32,
 }
 
+/// The negotiated features of the device that affect the requests.
 #[derive(Debug, Copy, Clone)]
-#[repr(C)]
 pub struct VirtioBlockFeature {
     support_flush: bool,
+    support_mq: bool,
+    support_discard: bool,
+    support_write_zeroes: bool,
 }
 
 impl VirtioBlockConfig {
@@ -174,12 +177,53 @@ impl ConfigManager<VirtioBlockConfig> {
             .unwrap();
 
         if self.is_modern() {
-            // TODO: read more field if modern interface exists.
+            blk_config.writeback = self
+                .read_once::<u8>(offset_of!(VirtioBlockConfig, writeback))
+                .unwrap();
+            blk_config.num_queues = self.num_queues();
+            blk_config.max_discard_sectors = self.max_discard_sectors();
+            blk_config.max_discard_seg = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_discard_seg))
+                .unwrap();
+            blk_config.discard_sector_alignment = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, discard_sector_alignment))
+                .unwrap();
+            blk_config.max_write_zeroes_sectors = self.max_write_zeroes_sectors();
+            blk_config.max_write_zeroes_seg = self
+                .read_once::<u32>(offset_of!(VirtioBlockConfig, max_write_zeroes_seg))
+                .unwrap();
+            blk_config.write_zeros_may_unmap = self
+                .read_once::<u8>(offset_of!(VirtioBlockConfig, write_zeros_may_unmap))
+                .unwrap();
         }
 
         blk_config
     }
 
+    /// Returns the number of request virtqueues.
+    ///
+    /// The field is valid only if `VIRTIO_BLK_F_MQ` is negotiated.
+    pub(self) fn num_queues(&self) -> u16 {
+        self.read_once::<u16>(offset_of!(VirtioBlockConfig, num_queues))
+            .unwrap()
+    }
+
+    /// Returns the maximum number of sectors in a discard command.
+    ///
+    /// The field is valid only if `VIRTIO_BLK_F_DISCARD` is negotiated.
+    pub(self) fn max_discard_sectors(&self) -> u32 {
+        self.read_once::<u32>(offset_of!(VirtioBlockConfig, max_discard_sectors))
+            .unwrap()
+    }
+
+    /// Returns the maximum number of sectors in a write zeroes command.
+    ///
+    /// The field is valid only if `VIRTIO_BLK_F_WRITE_ZEROES` is negotiated.
+    pub(self) fn max_write_zeroes_sectors(&self) -> u32 {
+        self.read_once::<u32>(offset_of!(VirtioBlockConfig, max_write_zeroes_sectors))
+            .unwrap()
+    }
+
     pub(self) fn block_size(&self) -> usize {
         self.read_once::<u32>(offset_of!(VirtioBlockConfig, blk_size))
             .unwrap() as usize
@@ -199,7 +243,16 @@ impl ConfigManager<VirtioBlockConfig> {
 
 impl VirtioBlockFeature {
     pub(self) fn new(transport: &dyn VirtioTransport) -> Self {
-        let support_flush = transport.read_device_features() & BlockFeatures::FLUSH.bits() == 1;
-        VirtioBlockFeature { support_flush }
+        // All the features offered by the device are accepted, except those removed in
+        // `BlockDevice::negotiate_features`.
+        let features = BlockFeatures::from_bits_truncate(device::BlockDevice::negotiate_features(
+            transport.read_device_features(),
+        ));
+        VirtioBlockFeature {
+            support_flush: features.contains(BlockFeatures::FLUSH),
+            support_mq: features.contains(BlockFeatures::MQ),
+            support_discard: features.contains(BlockFeatures::DISCARD),
+            support_write_zeroes: features.contains(BlockFeatures::WRITE_ZEROES),
+        }
     }
 }
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The control messages of the console devices with `VIRTIO_CONSOLE_F_MULTIPORT`.
+
+use int_to_c_enum::TryFromInt;
+use ostd::Pod;
+
+/// The header of a control message.
+///
+/// The header may be followed by the payload of the event, e.g., the name of the port.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct VirtioConsoleControl {
+    /// The port ID.
+    pub id: u32,
+    pub event: u16,
+    pub value: u16,
+}
+
+/// The events of the control messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u16)]
+pub(super) enum ControlEvent {
+    /// Sent by the driver when it is ready to receive the control messages.
+    DeviceReady = 0,
+    /// Sent by the device to add a port.
+    DeviceAdd = 1,
+    /// Sent by the device to remove a port.
+    DeviceRemove = 2,
+    /// Sent by the driver when a port is ready (or fails to be added).
+    PortReady = 3,
+    /// Sent by the device to tell that a port is a console port.
+    ConsolePort = 4,
+    /// Sent by the device to tell the window size of a console port.
+    Resize = 5,
+    /// Sent by either side when the port is opened or closed on that side.
+    PortOpen = 6,
+    /// Sent by the device to tell the name of a port.
+    PortName = 7,
+}
+
+/// The payload of [`ControlEvent::Resize`].
+///
+/// The rows come before the columns, which is the layout used by Linux and QEMU, although the
+/// VirtIO spec says the opposite.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct VirtioConsoleResize {
+    pub rows: u16,
+    pub cols: u16,
+}
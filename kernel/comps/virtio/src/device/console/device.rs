@@ -1,78 +1,114 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{boxed::Box, fmt::Debug, string::ToString, sync::Arc, vec::Vec};
-use core::hint::spin_loop;
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    fmt::Debug,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    hint::spin_loop,
+    mem::size_of,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
 
-use aster_console::{AnyConsoleDevice, ConsoleCallback};
-use log::debug;
+use log::{debug, info, warn};
 use ostd::{
-    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmReader},
-    sync::{Rcu, SpinLock},
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmReader, PAGE_SIZE},
+    sync::SpinLock,
     trap::TrapFrame,
+    Pod,
 };
 
-use super::{config::VirtioConsoleConfig, DEVICE_NAME};
+use super::{
+    config::VirtioConsoleConfig,
+    control::{ControlEvent, VirtioConsoleControl, VirtioConsoleResize},
+    port::ConsolePort,
+    DEVICE_NAME,
+};
 use crate::{
     device::{console::config::ConsoleFeatures, VirtioDeviceError},
     queue::VirtQueue,
     transport::{ConfigManager, VirtioTransport},
 };
 
+/// The maximum number of the ports of a device that are supported.
+const MAX_NR_PORTS: u32 = 32;
+
+/// The number of the buffers in the control receive queue.
+///
+/// QEMU drops the control messages if there are no available buffers, and it sends the
+/// messages of all the ports at once when the driver is ready. So there should be enough
+/// buffers for all the ports.
+const CONTROL_QUEUE_SIZE: u16 = 32;
+/// The size of each buffer in the control receive queue.
+///
+/// The port names that do not fit in the buffer are truncated.
+const CONTROL_BUFFER_LEN: usize = PAGE_SIZE / CONTROL_QUEUE_SIZE as usize;
+
+const RECV0_QUEUE_INDEX: u16 = 0;
+const TRANSMIT0_QUEUE_INDEX: u16 = 1;
+const CONTROL_RECV_QUEUE_INDEX: u16 = 2;
+const CONTROL_TRANSMIT_QUEUE_INDEX: u16 = 3;
+
+/// Whether a console port has been registered as the console device of the kernel.
+static HAS_KERNEL_CONSOLE: AtomicBool = AtomicBool::new(false);
+
 pub struct ConsoleDevice {
+    /// The index of the device, which is used in the names of the ports.
+    index: usize,
     config_manager: ConfigManager<VirtioConsoleConfig>,
+    features: ConsoleFeatures,
     transport: SpinLock<Box<dyn VirtioTransport>>,
-    receive_queue: SpinLock<VirtQueue>,
+    /// The queues of the ports, indexed by the port IDs.
+    port_queues: Vec<PortQueues>,
+    /// The control queues, which exist only if `VIRTIO_CONSOLE_F_MULTIPORT` is negotiated.
+    control_queues: Option<ControlQueues>,
+    ports: SpinLock<BTreeMap<u32, Arc<ConsolePort>>>,
+    weak_self: Weak<Self>,
+}
+
+struct PortQueues {
+    receive: SpinLock<ReceiveState>,
     transmit_queue: SpinLock<VirtQueue>,
     send_buffer: DmaStream,
     receive_buffer: DmaStream,
-    #[expect(clippy::box_collection)]
-    callbacks: Rcu<Box<Vec<&'static ConsoleCallback>>>,
 }
 
-impl AnyConsoleDevice for ConsoleDevice {
-    fn send(&self, value: &[u8]) {
-        let mut transmit_queue = self.transmit_queue.disable_irq().lock();
-        let mut reader = VmReader::from(value);
-
-        while reader.remain() > 0 {
-            let mut writer = self.send_buffer.writer().unwrap();
-            let len = writer.write(&mut reader);
-            self.send_buffer.sync(0..len).unwrap();
-
-            let slice = DmaStreamSlice::new(&self.send_buffer, 0, len);
-            transmit_queue.add_dma_buf(&[&slice], &[]).unwrap();
+struct ReceiveState {
+    queue: VirtQueue,
+    buffer: BufferState,
+    is_throttled: bool,
+}
 
-            if transmit_queue.should_notify() {
-                transmit_queue.notify();
-            }
-            while !transmit_queue.can_pop() {
-                spin_loop();
-            }
-            transmit_queue.pop_used().unwrap();
-        }
-    }
+/// The state of the receive buffer of a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BufferState {
+    /// The buffer is not in the receive queue.
+    Idle,
+    /// The buffer is in the receive queue.
+    Added,
+    /// The received bytes in the buffer are being delivered.
+    Busy,
+}
 
-    fn register_callback(&self, callback: &'static ConsoleCallback) {
-        loop {
-            let callbacks = self.callbacks.read();
-            let mut callbacks_cloned = callbacks.get().clone();
-            callbacks_cloned.push(callback);
-            if callbacks.compare_exchange(callbacks_cloned).is_ok() {
-                break;
-            }
-            // Contention on pushing, retry.
-            core::hint::spin_loop();
-        }
-    }
+struct ControlQueues {
+    receive_queue: SpinLock<VirtQueue>,
+    transmit_queue: SpinLock<VirtQueue>,
+    receive_buffers: DmaStream,
+    send_buffer: DmaStream,
 }
 
 impl Debug for ConsoleDevice {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("ConsoleDevice")
+            .field("index", &self.index)
             .field("config", &self.config_manager.read_config())
+            .field("features", &self.features)
             .field("transport", &self.transport)
-            .field("receive_queue", &self.receive_queue)
-            .field("transmit_queue", &self.transmit_queue)
+            .field("ports", &self.ports)
             .finish()
     }
 }
@@ -80,101 +116,465 @@ impl Debug for ConsoleDevice {
 impl ConsoleDevice {
     pub fn negotiate_features(features: u64) -> u64 {
         let mut features = ConsoleFeatures::from_bits_truncate(features);
-        // A virtio console device may have multiple ports, but we only use one port to communicate now.
-        features.remove(ConsoleFeatures::VIRTIO_CONSOLE_F_MULTIPORT);
+        // The emergency write is never used.
+        features.remove(ConsoleFeatures::VIRTIO_CONSOLE_F_EMERG_WRITE);
         features.bits()
     }
 
     pub fn init(mut transport: Box<dyn VirtioTransport>) -> Result<(), VirtioDeviceError> {
         let config_manager = VirtioConsoleConfig::new_manager(transport.as_ref());
-        debug!("virtio_console_config = {:?}", config_manager.read_config());
-
-        const RECV0_QUEUE_INDEX: u16 = 0;
-        const TRANSMIT0_QUEUE_INDEX: u16 = 1;
-        let receive_queue =
-            SpinLock::new(VirtQueue::new(RECV0_QUEUE_INDEX, 2, transport.as_mut()).unwrap());
-        let transmit_queue =
-            SpinLock::new(VirtQueue::new(TRANSMIT0_QUEUE_INDEX, 2, transport.as_mut()).unwrap());
-
-        let send_buffer = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
-            DmaStream::map(segment.into(), DmaDirection::ToDevice, false).unwrap()
+        let config = config_manager.read_config();
+        debug!("virtio_console_config = {:?}", config);
+
+        let features = ConsoleFeatures::from_bits_truncate(Self::negotiate_features(
+            transport.read_device_features(),
+        ));
+        let is_multiport = features.contains(ConsoleFeatures::VIRTIO_CONSOLE_F_MULTIPORT);
+
+        // Port 0 uses queues 0 and 1, the control queues are 2 and 3, and port N (N >= 1) uses
+        // queues 2N + 2 and 2N + 3.
+        let nr_ports = if is_multiport {
+            let max_nr_queues = (transport.num_queues() as u32).saturating_sub(2) / 2;
+            config
+                .max_nr_ports
+                .clamp(1, MAX_NR_PORTS)
+                .min(max_nr_queues)
+        } else {
+            1
         };
 
-        let receive_buffer = {
-            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
-            DmaStream::map(segment.into(), DmaDirection::FromDevice, false).unwrap()
+        let mut port_queues = Vec::with_capacity(nr_ports as usize);
+        for port_id in 0..nr_ports {
+            let (receive_index, transmit_index) = port_queue_indexes(port_id);
+            port_queues.push(PortQueues {
+                receive: SpinLock::new(ReceiveState {
+                    queue: VirtQueue::new(receive_index, 2, transport.as_mut()).unwrap(),
+                    buffer: BufferState::Idle,
+                    is_throttled: false,
+                }),
+                transmit_queue: SpinLock::new(
+                    VirtQueue::new(transmit_index, 2, transport.as_mut()).unwrap(),
+                ),
+                send_buffer: alloc_dma_stream(DmaDirection::ToDevice),
+                receive_buffer: alloc_dma_stream(DmaDirection::FromDevice),
+            });
+        }
+
+        let control_queues = if is_multiport {
+            let mut receive_queue = VirtQueue::new(
+                CONTROL_RECV_QUEUE_INDEX,
+                CONTROL_QUEUE_SIZE,
+                transport.as_mut(),
+            )
+            .unwrap();
+            let receive_buffers = alloc_dma_stream(DmaDirection::FromDevice);
+            for i in 0..CONTROL_QUEUE_SIZE {
+                let slice = control_buffer(&receive_buffers, i);
+                let token = receive_queue.add_dma_buf(&[], &[&slice]).unwrap();
+                assert_eq!(token, i);
+            }
+            let transmit_queue =
+                VirtQueue::new(CONTROL_TRANSMIT_QUEUE_INDEX, 2, transport.as_mut()).unwrap();
+
+            Some(ControlQueues {
+                receive_queue: SpinLock::new(receive_queue),
+                transmit_queue: SpinLock::new(transmit_queue),
+                receive_buffers,
+                send_buffer: alloc_dma_stream(DmaDirection::ToDevice),
+            })
+        } else {
+            None
         };
 
-        let device = Arc::new(Self {
+        // There can be multiple console devices.
+        static NR_DEVICES: AtomicUsize = AtomicUsize::new(0);
+        let index = NR_DEVICES.fetch_add(1, Ordering::Relaxed);
+
+        let device = Arc::new_cyclic(|weak_self| Self {
+            index,
             config_manager,
+            features,
             transport: SpinLock::new(transport),
-            receive_queue,
-            transmit_queue,
-            send_buffer,
-            receive_buffer,
-            callbacks: Rcu::new(Box::new(Vec::new())),
+            port_queues,
+            control_queues,
+            ports: SpinLock::new(BTreeMap::new()),
+            weak_self: weak_self.clone(),
         });
 
-        device.activate_receive_buffer(&mut device.receive_queue.disable_irq().lock());
+        // Port 0 is created eagerly, so that the console is usable before the ports are
+        // announced by the device. It is always the console port without
+        // `VIRTIO_CONSOLE_F_MULTIPORT`, and QEMU reserves it for the console port otherwise.
+        let port0 = ConsolePort::new(0, device.weak_self.clone(), !is_multiport, !is_multiport);
+        device.ports.disable_irq().lock().insert(0, port0.clone());
+        device.add_receive_buffer(0);
+        if !is_multiport {
+            device.update_console_size();
+        }
 
         // Register irq callbacks
         let mut transport = device.transport.disable_irq().lock();
-        let handle_console_input = {
+        for port_id in 0..nr_ports {
+            let handle_port_input = {
+                let device = device.clone();
+                move |_: &TrapFrame| device.handle_recv_irq(port_id)
+            };
+            transport
+                .register_queue_callback(
+                    port_queue_indexes(port_id).0,
+                    Box::new(handle_port_input),
+                    false,
+                )
+                .unwrap();
+        }
+        if is_multiport {
+            let handle_control = {
+                let device = device.clone();
+                move |_: &TrapFrame| device.handle_control_irq()
+            };
+            transport
+                .register_queue_callback(CONTROL_RECV_QUEUE_INDEX, Box::new(handle_control), false)
+                .unwrap();
+        }
+        let config_space_change = {
             let device = device.clone();
-            move |_: &TrapFrame| device.handle_recv_irq()
+            move |_: &TrapFrame| device.handle_config_change()
         };
-        transport
-            .register_queue_callback(RECV0_QUEUE_INDEX, Box::new(handle_console_input), false)
-            .unwrap();
         transport
             .register_cfg_callback(Box::new(config_space_change))
             .unwrap();
         transport.finish_init();
         drop(transport);
 
-        aster_console::register_device(DEVICE_NAME.to_string(), device);
+        register_kernel_console(&port0);
+        super::add_port(port0);
+
+        if is_multiport {
+            device.send_control(0, ControlEvent::DeviceReady, 1);
+        }
 
         Ok(())
     }
 
-    fn handle_recv_irq(&self) {
-        let mut receive_queue = self.receive_queue.disable_irq().lock();
+    pub(super) fn index(&self) -> usize {
+        self.index
+    }
 
-        let Ok((_, len)) = receive_queue.pop_used() else {
+    fn is_multiport(&self) -> bool {
+        self.control_queues.is_some()
+    }
+
+    fn port(&self, port_id: u32) -> Option<Arc<ConsolePort>> {
+        self.ports.disable_irq().lock().get(&port_id).cloned()
+    }
+
+    /// Sends the bytes to the port and waits until the device consumes them.
+    pub(super) fn send(&self, port_id: u32, bytes: &[u8]) {
+        let Some(queues) = self.port_queues.get(port_id as usize) else {
             return;
         };
-        self.receive_buffer.sync(0..len as usize).unwrap();
+        let mut transmit_queue = queues.transmit_queue.disable_irq().lock();
+        let mut reader = VmReader::from(bytes);
+
+        while reader.remain() > 0 {
+            let mut writer = queues.send_buffer.writer().unwrap();
+            let len = writer.write(&mut reader);
+            queues.send_buffer.sync(0..len).unwrap();
+
+            let slice = DmaStreamSlice::new(&queues.send_buffer, 0, len);
+            transmit_queue.add_dma_buf(&[&slice], &[]).unwrap();
+
+            if transmit_queue.should_notify() {
+                transmit_queue.notify();
+            }
+            while !transmit_queue.can_pop() {
+                spin_loop();
+            }
+            transmit_queue.pop_used().unwrap();
+        }
+    }
+
+    pub(super) fn send_port_open(&self, port_id: u32, is_open: bool) {
+        if self.is_multiport() {
+            self.send_control(port_id, ControlEvent::PortOpen, is_open as u16);
+        }
+    }
+
+    pub(super) fn set_port_throttled(&self, port_id: u32, is_throttled: bool) {
+        let Some(queues) = self.port_queues.get(port_id as usize) else {
+            return;
+        };
+        queues.receive.disable_irq().lock().is_throttled = is_throttled;
+
+        if !is_throttled {
+            self.add_receive_buffer(port_id);
+        }
+    }
+
+    fn handle_recv_irq(&self, port_id: u32) {
+        let queues = &self.port_queues[port_id as usize];
+
+        let len = {
+            let mut receive = queues.receive.disable_irq().lock();
+            let Ok((_, len)) = receive.queue.pop_used() else {
+                return;
+            };
+            receive.buffer = BufferState::Busy;
+            len as usize
+        };
+        queues.receive_buffer.sync(0..len).unwrap();
 
-        let callbacks = self.callbacks.read();
-        for callback in callbacks.get().iter() {
-            let mut reader = self.receive_buffer.reader().unwrap();
-            reader.limit(len as usize);
-            callback(reader);
+        if let Some(port) = self.port(port_id) {
+            let mut reader = queues.receive_buffer.reader().unwrap();
+            reader.limit(len);
+            port.handle_receive(reader);
         }
-        drop(callbacks);
 
-        self.activate_receive_buffer(&mut receive_queue);
+        queues.receive.disable_irq().lock().buffer = BufferState::Idle;
+        self.add_receive_buffer(port_id);
     }
 
-    fn activate_receive_buffer(&self, receive_queue: &mut VirtQueue) {
-        receive_queue
-            // We limit the buffer length to one to work around a QEMU bug that causes incorrect
-            // results when pasting more than 32 bytes into the virtio console. This has no
-            // performance penalty, since QEMU always gets one byte at a time, regardless of
-            // whether we have this limit or not.
-            //
-            // For the QEMU bug, see details at
-            // <https://lore.kernel.org/qemu-devel/20240707111940.232549-3-lrh2000@pku.edu.cn/T/#u>.
-            .add_dma_buf(&[], &[&DmaStreamSlice::new(&self.receive_buffer, 0, 1)])
+    /// Adds the receive buffer of the port to the receive queue, unless it is already added or
+    /// the port is throttled.
+    fn add_receive_buffer(&self, port_id: u32) {
+        let queues = &self.port_queues[port_id as usize];
+        let mut receive = queues.receive.disable_irq().lock();
+        if receive.buffer != BufferState::Idle || receive.is_throttled {
+            return;
+        }
+
+        let is_console = self.port(port_id).is_some_and(|port| port.is_console());
+        // For console ports, we limit the buffer length to one to work around a QEMU bug that
+        // causes incorrect results when pasting more than 32 bytes into the virtio console.
+        // This has no performance penalty, since QEMU always gets one byte at a time,
+        // regardless of whether we have this limit or not.
+        //
+        // For the QEMU bug, see details at
+        // <https://lore.kernel.org/qemu-devel/20240707111940.232549-3-lrh2000@pku.edu.cn/T/#u>.
+        let len = if is_console { 1 } else { PAGE_SIZE };
+        receive
+            .queue
+            .add_dma_buf(&[], &[&DmaStreamSlice::new(&queues.receive_buffer, 0, len)])
             .unwrap();
+        receive.buffer = BufferState::Added;
+
+        if receive.queue.should_notify() {
+            receive.queue.notify();
+        }
+    }
+
+    fn handle_config_change(&self) {
+        debug!("Virtio-Console device configuration space change");
+
+        // With `VIRTIO_CONSOLE_F_MULTIPORT`, the size is reported by the control messages.
+        if !self.is_multiport() {
+            self.update_console_size();
+        }
+    }
+
+    fn update_console_size(&self) {
+        if !self
+            .features
+            .contains(ConsoleFeatures::VIRTIO_CONSOLE_F_SIZE)
+        {
+            return;
+        }
+
+        let config = self.config_manager.read_config();
+        if let Some(port) = self.port(0) {
+            port.resize(config.rows, config.cols);
+        }
+    }
+
+    fn handle_control_irq(&self) {
+        let Some(control_queues) = &self.control_queues else {
+            return;
+        };
+
+        // The messages are copied out, so that the buffers can be reused while handling the
+        // messages, which may cause the device to send more messages.
+        let mut messages = Vec::new();
+        {
+            let mut receive_queue = control_queues.receive_queue.disable_irq().lock();
+            while let Ok((token, len)) = receive_queue.pop_used() {
+                let offset = token as usize * CONTROL_BUFFER_LEN;
+                let len = (len as usize).min(CONTROL_BUFFER_LEN);
+                control_queues
+                    .receive_buffers
+                    .sync(offset..offset + len)
+                    .unwrap();
+
+                let mut message = alloc::vec![0u8; len];
+                let mut reader = control_queues.receive_buffers.reader().unwrap();
+                reader.skip(offset).limit(len);
+                reader.read(&mut message.as_mut_slice().into());
+                messages.push(message);
+
+                let slice = control_buffer(&control_queues.receive_buffers, token);
+                let new_token = receive_queue.add_dma_buf(&[], &[&slice]).unwrap();
+                // This only works because nothing happen between `pop_used` and `add` that
+                // affects the list of free descriptors in the queue, so `add` reuses the
+                // descriptor which was just freed by `pop_used`.
+                assert_eq!(new_token, token);
+            }
+            if receive_queue.should_notify() {
+                receive_queue.notify();
+            }
+        }
 
-        if receive_queue.should_notify() {
-            receive_queue.notify();
+        for message in messages {
+            self.handle_control_message(&message);
         }
     }
+
+    fn handle_control_message(&self, message: &[u8]) {
+        const HEADER_LEN: usize = size_of::<VirtioConsoleControl>();
+
+        if message.len() < HEADER_LEN {
+            warn!("Virtio-Console: Truncated control message: {:?}", message);
+            return;
+        }
+        let header = VirtioConsoleControl::from_bytes(&message[..HEADER_LEN]);
+        let payload = &message[HEADER_LEN..];
+        let Ok(event) = ControlEvent::try_from(header.event) else {
+            warn!("Virtio-Console: Unknown control message: {:?}", header);
+            return;
+        };
+        debug!("Virtio-Console: Control message: {:?} {:?}", event, header);
+
+        if event == ControlEvent::DeviceAdd {
+            self.add_port(header.id);
+            return;
+        }
+
+        let Some(port) = self.port(header.id) else {
+            warn!(
+                "Virtio-Console: Control message for an unknown port: {:?}",
+                header
+            );
+            return;
+        };
+        match event {
+            ControlEvent::DeviceRemove => {
+                info!("Virtio-Console: Port {} is removed", header.id);
+                port.set_removed();
+            }
+            ControlEvent::ConsolePort => {
+                if header.value == 0 || !port.set_console() {
+                    return;
+                }
+                // Like Linux, the console ports are always open on the guest side.
+                port.set_guest_connected(true);
+                super::notify_port(&port);
+            }
+            ControlEvent::Resize => {
+                if payload.len() < size_of::<VirtioConsoleResize>() {
+                    return;
+                }
+                let size =
+                    VirtioConsoleResize::from_bytes(&payload[..size_of::<VirtioConsoleResize>()]);
+                port.resize(size.rows, size.cols);
+            }
+            ControlEvent::PortOpen => port.set_host_connected(header.value != 0),
+            ControlEvent::PortName => {
+                let name = String::from_utf8_lossy(payload);
+                port.set_name(String::from(name.trim_end_matches('\0')));
+            }
+            ControlEvent::DeviceReady | ControlEvent::DeviceAdd | ControlEvent::PortReady => {
+                warn!("Virtio-Console: Unexpected control message: {:?}", header);
+            }
+        }
+    }
+
+    fn add_port(&self, port_id: u32) {
+        if port_id as usize >= self.port_queues.len() {
+            warn!(
+                "Virtio-Console: Port {} exceeds the maximum number of ports",
+                port_id
+            );
+            self.send_control(port_id, ControlEvent::PortReady, 0);
+            return;
+        }
+
+        // A port that is added again after being removed is reused, so that the users of the
+        // port see it come back.
+        let (port, is_new) = {
+            let mut ports = self.ports.disable_irq().lock();
+            match ports.get(&port_id) {
+                Some(port) if port.is_removed() => (port.clone(), port.reset()),
+                Some(port) => (port.clone(), false),
+                None => {
+                    let port = ConsolePort::new(port_id, self.weak_self.clone(), false, false);
+                    ports.insert(port_id, port.clone());
+                    (port, true)
+                }
+            }
+        };
+        self.add_receive_buffer(port_id);
+
+        if is_new {
+            info!("Virtio-Console: Port {} is added", port_id);
+            super::add_port(port);
+        }
+        self.send_control(port_id, ControlEvent::PortReady, 1);
+    }
+
+    fn send_control(&self, port_id: u32, event: ControlEvent, value: u16) {
+        let Some(control_queues) = &self.control_queues else {
+            return;
+        };
+
+        let message = VirtioConsoleControl {
+            id: port_id,
+            event: event as u16,
+            value,
+        };
+        let len = size_of::<VirtioConsoleControl>();
+
+        let mut transmit_queue = control_queues.transmit_queue.disable_irq().lock();
+        let mut writer = control_queues.send_buffer.writer().unwrap();
+        writer.write(&mut VmReader::from(message.as_bytes()));
+        control_queues.send_buffer.sync(0..len).unwrap();
+
+        let slice = DmaStreamSlice::new(&control_queues.send_buffer, 0, len);
+        transmit_queue.add_dma_buf(&[&slice], &[]).unwrap();
+        if transmit_queue.should_notify() {
+            transmit_queue.notify();
+        }
+        while !transmit_queue.can_pop() {
+            spin_loop();
+        }
+        transmit_queue.pop_used().unwrap();
+    }
+}
+
+/// Returns the indexes of the receive queue and the transmit queue of the port.
+fn port_queue_indexes(port_id: u32) -> (u16, u16) {
+    if port_id == 0 {
+        (RECV0_QUEUE_INDEX, TRANSMIT0_QUEUE_INDEX)
+    } else {
+        let receive_index = (port_id * 2 + 2) as u16;
+        (receive_index, receive_index + 1)
+    }
+}
+
+/// Registers the port as the console device of the kernel, unless another port is registered.
+fn register_kernel_console(port: &Arc<ConsolePort>) {
+    if HAS_KERNEL_CONSOLE.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    port.set_kernel_console();
+    aster_console::register_device(DEVICE_NAME.to_string(), port.clone());
+}
+
+fn alloc_dma_stream(direction: DmaDirection) -> DmaStream {
+    let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+    DmaStream::map(segment.into(), direction, false).unwrap()
 }
 
-fn config_space_change(_: &TrapFrame) {
-    debug!("Virtio-Console device configuration space change");
+fn control_buffer(buffers: &DmaStream, index: u16) -> DmaStreamSlice<&DmaStream> {
+    DmaStreamSlice::new(
+        buffers,
+        index as usize * CONTROL_BUFFER_LEN,
+        CONTROL_BUFFER_LEN,
+    )
 }
@@ -1,6 +1,65 @@
 // SPDX-License-Identifier: MPL-2.0
 
+//! The virtio console devices.
+//!
+//! A console device has one or more ports. Without `VIRTIO_CONSOLE_F_MULTIPORT`, port 0 is the
+//! only port and it is a console port. With the feature, the ports are announced (and removed)
+//! by the device through the control queues, and the device tells which of them are console
+//! ports. The other ports are generic ports that serve as side channels between the host and the
+//! guest, e.g., for guest agents.
+//!
+//! The first console port is registered as a console device of the kernel. All the ports are
+//! available through [`all_ports`], and the users are notified of the new ports via the callbacks
+//! registered by [`register_port_callback`].
+
+use alloc::{sync::Arc, vec::Vec};
+
+use ostd::sync::{LocalIrqDisabled, RwLock};
+
 pub mod config;
+mod control;
 pub mod device;
+pub mod port;
+
+pub use port::{ConsolePort, ConsolePortListener};
 
 pub static DEVICE_NAME: &str = "Virtio-Console";
+
+/// The callback that is called when a port is added or becomes a console port.
+///
+/// The callback is called in the interrupt context, so it must not sleep.
+pub type PortCallback = dyn Fn(&Arc<ConsolePort>) + Send + Sync;
+
+/// The ports of all the console devices.
+static PORTS: RwLock<Vec<Arc<ConsolePort>>, LocalIrqDisabled> = RwLock::new(Vec::new());
+
+static PORT_CALLBACKS: RwLock<Vec<&'static PortCallback>, LocalIrqDisabled> =
+    RwLock::new(Vec::new());
+
+/// Returns all the ports that have been added, including the removed ones.
+///
+/// A port that is removed and then added again is the same [`ConsolePort`].
+pub fn all_ports() -> Vec<Arc<ConsolePort>> {
+    PORTS.read().clone()
+}
+
+/// Registers a callback that is called when a port is added or becomes a console port.
+pub fn register_port_callback(callback: &'static PortCallback) {
+    PORT_CALLBACKS.write().push(callback);
+}
+
+fn add_port(port: Arc<ConsolePort>) {
+    {
+        let mut ports = PORTS.write();
+        if !ports.iter().any(|added| Arc::ptr_eq(added, &port)) {
+            ports.push(port.clone());
+        }
+    }
+    notify_port(&port);
+}
+
+fn notify_port(port: &Arc<ConsolePort>) {
+    for callback in PORT_CALLBACKS.read().iter() {
+        callback(port);
+    }
+}
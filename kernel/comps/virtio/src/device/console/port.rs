@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    boxed::Box,
+    fmt::Debug,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use aster_console::{AnyConsoleDevice, ConsoleCallback};
+use ostd::{
+    mm::{Infallible, VmReader},
+    sync::{LocalIrqDisabled, Rcu, SpinLock},
+};
+
+use super::device::ConsoleDevice;
+
+/// The listener of the events of a console port.
+///
+/// The methods are called in the interrupt context, so they must not sleep.
+pub trait ConsolePortListener: Send + Sync {
+    /// Called when some bytes are received.
+    fn on_receive(&self, bytes: &[u8]);
+
+    /// Called when the host changes the window size of the console port.
+    fn on_resize(&self, rows: u16, cols: u16);
+
+    /// Called when the host side of the port is opened or closed.
+    fn on_host_connection(&self, is_connected: bool);
+}
+
+/// A port of a virtio console device.
+pub struct ConsolePort {
+    id: u32,
+    device: Weak<ConsoleDevice>,
+    state: SpinLock<PortState, LocalIrqDisabled>,
+    listener: SpinLock<Option<Weak<dyn ConsolePortListener>>, LocalIrqDisabled>,
+    /// The callbacks registered by the console layer.
+    #[expect(clippy::box_collection)]
+    callbacks: Rcu<Box<Vec<&'static ConsoleCallback>>>,
+}
+
+#[derive(Debug, Default)]
+struct PortState {
+    name: Option<String>,
+    is_console: bool,
+    /// Whether the port is registered as the console device of the kernel.
+    is_kernel_console: bool,
+    is_host_connected: bool,
+    is_guest_connected: bool,
+    is_removed: bool,
+    /// The window size of a console port, in rows and columns.
+    window_size: Option<(u16, u16)>,
+}
+
+impl ConsolePort {
+    pub(super) fn new(
+        id: u32,
+        device: Weak<ConsoleDevice>,
+        is_console: bool,
+        is_host_connected: bool,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            id,
+            device,
+            state: SpinLock::new(PortState {
+                is_console,
+                is_host_connected,
+                ..Default::default()
+            }),
+            listener: SpinLock::new(None),
+            callbacks: Rcu::new(Box::new(Vec::new())),
+        })
+    }
+
+    /// Returns the index of the console device that the port belongs to.
+    pub fn device_index(&self) -> usize {
+        self.device.upgrade().map_or(0, |device| device.index())
+    }
+
+    /// Returns the port ID, which is unique in the console device.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the name of the port given by the host, if any.
+    pub fn name(&self) -> Option<String> {
+        self.state.lock().name.clone()
+    }
+
+    /// Returns whether the port is a console port.
+    pub fn is_console(&self) -> bool {
+        self.state.lock().is_console
+    }
+
+    /// Returns whether the port is registered as the console device of the kernel.
+    ///
+    /// The input of such a port is delivered to the console of the kernel.
+    pub fn is_kernel_console(&self) -> bool {
+        self.state.lock().is_kernel_console
+    }
+
+    /// Returns whether the host side of the port is opened.
+    pub fn is_host_connected(&self) -> bool {
+        self.state.lock().is_host_connected
+    }
+
+    /// Returns whether the port has been removed by the host.
+    pub fn is_removed(&self) -> bool {
+        self.state.lock().is_removed
+    }
+
+    /// Returns the window size of the console port in rows and columns, if it is known.
+    pub fn window_size(&self) -> Option<(u16, u16)> {
+        self.state.lock().window_size
+    }
+
+    /// Sets the listener of the events.
+    pub fn set_listener(&self, listener: Weak<dyn ConsolePortListener>) {
+        *self.listener.lock() = Some(listener);
+    }
+
+    fn listener(&self) -> Option<Arc<dyn ConsolePortListener>> {
+        self.listener.lock().as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Writes the bytes to the port.
+    ///
+    /// The method returns after the bytes are consumed by the device, so it can be called in the
+    /// interrupt context. The bytes are discarded if the port has been removed.
+    pub fn write(&self, bytes: &[u8]) {
+        if self.is_removed() {
+            return;
+        }
+        if let Some(device) = self.device.upgrade() {
+            device.send(self.id, bytes);
+        }
+    }
+
+    /// Tells the host that the guest side of the port is opened or closed.
+    pub fn set_guest_connected(&self, is_connected: bool) {
+        {
+            let mut state = self.state.lock();
+            if state.is_guest_connected == is_connected || state.is_removed {
+                return;
+            }
+            state.is_guest_connected = is_connected;
+        }
+
+        if let Some(device) = self.device.upgrade() {
+            device.send_port_open(self.id, is_connected);
+        }
+    }
+
+    /// Stops receiving the bytes, so that the host keeps the bytes until the port is unthrottled.
+    pub fn throttle(&self) {
+        if let Some(device) = self.device.upgrade() {
+            device.set_port_throttled(self.id, true);
+        }
+    }
+
+    /// Resumes receiving the bytes.
+    pub fn unthrottle(&self) {
+        if let Some(device) = self.device.upgrade() {
+            device.set_port_throttled(self.id, false);
+        }
+    }
+
+    pub(super) fn handle_receive(&self, reader: VmReader<Infallible>) {
+        let callbacks = self.callbacks.read();
+        for callback in callbacks.get().iter() {
+            callback(reader.clone());
+        }
+        drop(callbacks);
+
+        if let Some(listener) = self.listener() {
+            let mut reader = reader;
+            let mut bytes = alloc::vec![0u8; reader.remain()];
+            reader.read(&mut bytes.as_mut_slice().into());
+            listener.on_receive(&bytes);
+        }
+    }
+
+    pub(super) fn set_name(&self, name: String) {
+        self.state.lock().name = Some(name);
+    }
+
+    /// Marks the port as a console port.
+    ///
+    /// Returns `false` if the port is already a console port.
+    pub(super) fn set_console(&self) -> bool {
+        !core::mem::replace(&mut self.state.lock().is_console, true)
+    }
+
+    pub(super) fn set_kernel_console(&self) {
+        self.state.lock().is_kernel_console = true;
+    }
+
+    pub(super) fn set_host_connected(&self, is_connected: bool) {
+        {
+            let mut state = self.state.lock();
+            if state.is_host_connected == is_connected {
+                return;
+            }
+            state.is_host_connected = is_connected;
+        }
+
+        if let Some(listener) = self.listener() {
+            listener.on_host_connection(is_connected);
+        }
+    }
+
+    pub(super) fn resize(&self, rows: u16, cols: u16) {
+        self.state.lock().window_size = Some((rows, cols));
+
+        if let Some(listener) = self.listener() {
+            listener.on_resize(rows, cols);
+        }
+    }
+
+    /// Resets the state of a removed port, which is added again.
+    ///
+    /// Returns `true` if the port is reset.
+    pub(super) fn reset(&self) -> bool {
+        let mut state = self.state.lock();
+        if !state.is_removed {
+            return false;
+        }
+        *state = PortState::default();
+        true
+    }
+
+    pub(super) fn set_removed(&self) {
+        {
+            let mut state = self.state.lock();
+            state.is_removed = true;
+            state.is_host_connected = false;
+        }
+
+        if let Some(listener) = self.listener() {
+            listener.on_host_connection(false);
+        }
+    }
+}
+
+impl AnyConsoleDevice for ConsolePort {
+    fn send(&self, buf: &[u8]) {
+        self.write(buf);
+    }
+
+    fn register_callback(&self, callback: &'static ConsoleCallback) {
+        loop {
+            let callbacks = self.callbacks.read();
+            let mut callbacks_cloned = callbacks.get().clone();
+            callbacks_cloned.push(callback);
+            if callbacks.compare_exchange(callbacks_cloned).is_ok() {
+                break;
+            }
+            // Contention on pushing, retry.
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl Debug for ConsolePort {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ConsolePort")
+            .field("id", &self.id)
+            .field("state", &*self.state.lock())
+            .finish()
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::string::String;
+use core::mem::offset_of;
+
+use aster_util::safe_ptr::SafePtr;
+use ostd::Pod;
+
+use crate::transport::{ConfigManager, VirtioTransport};
+
+bitflags::bitflags! {
+    pub struct FileSystemFeatures: u64 {
+        /// Device has support for FUSE notify messages.
+        const VIRTIO_FS_F_NOTIFICATION = 1 << 0;
+    }
+}
+
+/// The maximum length of the tag, which is not NUL-terminated if it is this long.
+pub const TAG_LEN: usize = 36;
+
+#[derive(Debug, Pod, Clone, Copy)]
+#[repr(C)]
+pub struct VirtioFileSystemConfig {
+    /// The name of the file system, encoded in UTF-8 and padded with NUL bytes.
+    pub tag: [u8; TAG_LEN],
+    /// The number of request virtqueues, excluding the high-priority queue.
+    pub num_request_queues: u32,
+    /// The size of the notification buffers.
+    pub notify_buf_size: u32,
+}
+
+impl VirtioFileSystemConfig {
+    pub(super) fn new_manager(transport: &dyn VirtioTransport) -> ConfigManager<Self> {
+        let safe_ptr = transport
+            .device_config_mem()
+            .map(|mem| SafePtr::new(mem, 0));
+        let bar_space = transport.device_config_bar();
+        ConfigManager::new(safe_ptr, bar_space)
+    }
+}
+
+impl ConfigManager<VirtioFileSystemConfig> {
+    pub(super) fn read_config(&self) -> VirtioFileSystemConfig {
+        let mut fs_config = VirtioFileSystemConfig::new_uninit();
+
+        for (index, byte) in fs_config.tag.iter_mut().enumerate() {
+            *byte = self
+                .read_once::<u8>(offset_of!(VirtioFileSystemConfig, tag) + index)
+                .unwrap();
+        }
+        fs_config.num_request_queues = self
+            .read_once::<u32>(offset_of!(VirtioFileSystemConfig, num_request_queues))
+            .unwrap();
+        fs_config.notify_buf_size = self
+            .read_once::<u32>(offset_of!(VirtioFileSystemConfig, notify_buf_size))
+            .unwrap();
+
+        fs_config
+    }
+
+    /// Reads the tag, which is used as the source of the mount.
+    pub(super) fn read_tag(&self) -> String {
+        let tag = self.read_config().tag;
+        let len = tag.iter().position(|&b| b == 0).unwrap_or(TAG_LEN);
+        String::from_utf8_lossy(&tag[..len]).into_owned()
+    }
+}
@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The virtio file system device, which carries FUSE requests over virtqueues.
+//!
+//! The device is found by its tag, which is used as the source when the file system is mounted.
+//! The DAX window, which maps the host files into the guest memory, is optional and not used, so
+//! all the data is transferred with FUSE requests.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+
+use ostd::sync::SpinLock;
+
+use self::device::FileSystemDevice;
+
+pub mod config;
+pub mod device;
+
+pub static DEVICE_NAME: &str = "Virtio-FS";
+
+static DEVICE_TABLE: SpinLock<BTreeMap<String, Arc<FileSystemDevice>>> =
+    SpinLock::new(BTreeMap::new());
+
+pub fn register_device(tag: String, device: Arc<FileSystemDevice>) {
+    DEVICE_TABLE.disable_irq().lock().insert(tag, device);
+}
+
+pub fn get_device(tag: &str) -> Option<Arc<FileSystemDevice>> {
+    DEVICE_TABLE.disable_irq().lock().get(tag).cloned()
+}
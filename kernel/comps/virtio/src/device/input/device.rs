@@ -1,16 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{
-    boxed::Box,
-    string::{String, ToString},
-    sync::Arc,
-    vec::Vec,
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    iter, mem,
+    sync::atomic::{AtomicUsize, Ordering},
 };
-use core::{fmt::Debug, iter, mem};
 
 use aster_input::{
     key::{Key, KeyStatus},
-    InputEvent,
+    InputCapability, InputEvent, RelAxis,
 };
 use aster_util::{field_ptr, safe_ptr::SafePtr};
 use bitflags::bitflags;
@@ -140,7 +139,10 @@ impl InputDevice {
         transport.finish_init();
         drop(transport);
 
-        aster_input::register_device(super::DEVICE_NAME.to_string(), device);
+        // There can be multiple input devices (e.g., a keyboard and a mouse).
+        static NR_DEVICES: AtomicUsize = AtomicUsize::new(0);
+        let index = NR_DEVICES.fetch_add(1, Ordering::Relaxed);
+        aster_input::register_device(format!("{}{}", super::DEVICE_NAME, index), device);
 
         Ok(())
     }
@@ -207,6 +209,19 @@ impl InputDevice {
             .unwrap() as usize
     }
 
+    /// Queries the codes of the event type that the device supports.
+    fn query_config_ev_bits(&self, event_type: u8) -> Vec<u8> {
+        let size = self.select_config(InputConfigSelect::EvBits, event_type);
+
+        let mut bits = Vec::with_capacity(size);
+        let mut data_ptr = field_ptr!(&self.config, VirtioInputConfig, data).cast::<u8>();
+        for _ in 0..size {
+            bits.push(data_ptr.read_once().unwrap());
+            data_ptr.byte_add(1);
+        }
+        bits
+    }
+
     fn handle_irq(&self) {
         let callbacks = self.callbacks.read();
         // Returns true if there may be more events to handle
@@ -214,22 +229,31 @@ impl InputDevice {
             event.sync().unwrap();
             let event: VirtioInputEvent = event.read().unwrap();
 
-            match event.event_type {
-                0 => return false,
-                // Keyboard
-                1 => {}
-                // TODO: Support mouse device.
+            let event = match event.event_type {
+                event_type if event_type == SYN as u16 => InputEvent::Sync,
+                event_type if event_type == KEY as u16 => {
+                    let status = match event.value {
+                        1 => KeyStatus::Pressed,
+                        0 => KeyStatus::Released,
+                        // The repeated keys are generated by the input layer.
+                        _ => return true,
+                    };
+                    let Ok(key) = Key::try_from(event.code) else {
+                        debug!("unsupported key code: {}", event.code);
+                        return true;
+                    };
+                    InputEvent::KeyBoard(key, status)
+                }
+                event_type if event_type == REL as u16 => {
+                    let Ok(axis) = RelAxis::try_from(event.code) else {
+                        return true;
+                    };
+                    InputEvent::Relative(axis, event.value as i32)
+                }
+                // TODO: Support absolute axes (e.g., tablets).
                 _ => return true,
-            }
-
-            let status = match event.value {
-                1 => KeyStatus::Pressed,
-                0 => KeyStatus::Released,
-                _ => return false,
             };
-
-            let event = InputEvent::KeyBoard(Key::try_from(event.code).unwrap(), status);
-            info!("Input Event:{:?}", event);
+            debug!("Input Event:{:?}", event);
 
             for callback in callbacks.iter() {
                 callback(event);
@@ -296,6 +320,27 @@ impl aster_input::InputDevice for InputDevice {
     fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync)) {
         self.callbacks.write().push(Arc::new(function))
     }
+
+    fn capability(&self) -> InputCapability {
+        let mut capability = InputCapability::new();
+
+        for (byte_index, byte) in self.query_config_ev_bits(KEY).into_iter().enumerate() {
+            for bit in (0..8).filter(|bit| byte & (1 << bit) != 0) {
+                if let Ok(key) = Key::try_from((byte_index * 8 + bit) as u16) {
+                    capability.add_key(key);
+                }
+            }
+        }
+        for (byte_index, byte) in self.query_config_ev_bits(REL).into_iter().enumerate() {
+            for bit in (0..8).filter(|bit| byte & (1 << bit) != 0) {
+                if let Ok(axis) = RelAxis::try_from((byte_index * 8 + bit) as u16) {
+                    capability.add_rel_axis(axis);
+                }
+            }
+        }
+
+        capability
+    }
 }
 
 impl Debug for InputDevice {
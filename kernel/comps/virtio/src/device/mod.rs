@@ -6,9 +6,12 @@ use crate::queue::QueueError;
 
 pub mod block;
 pub mod console;
+pub mod filesystem;
 pub mod input;
 pub mod network;
 pub mod socket;
+pub mod sound;
+pub mod transport9p;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
 #[repr(u8)]
@@ -36,6 +39,8 @@ pub enum VirtioDeviceType {
     Pstore = 22,
     IOMMU = 23,
     Memory = 24,
+    Sound = 25,
+    FileSystem = 26,
 }
 
 #[derive(Debug)]
@@ -1,18 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{
-    boxed::Box, collections::linked_list::LinkedList, string::ToString, sync::Arc, vec::Vec,
-};
+use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
 use core::{fmt::Debug, mem::size_of};
 
 use aster_bigtcp::device::{Checksum, DeviceCapabilities, Medium};
 use aster_network::{
     AnyNetworkDevice, EthernetAddr, RxBuffer, TxBuffer, VirtioNetError, RX_BUFFER_POOL,
+    TX_BUFFER_POOL_SIZE,
 };
-use aster_softirq::BottomHalfDisabled;
 use aster_util::slot_vec::SlotVec;
 use log::{debug, warn};
-use ostd::{mm::DmaStream, sync::SpinLock, trap::TrapFrame};
+use ostd::{
+    mm::DmaStream,
+    sync::{RingBuffer, SpinLock},
+    trap::TrapFrame,
+};
+use spin::Once;
 
 use super::{config::VirtioNetConfig, header::VirtioNetHdr};
 use crate::{
@@ -194,7 +197,8 @@ impl NetworkDevice {
             return Err(VirtioNetError::Busy);
         }
 
-        let tx_buffer = TxBuffer::new(&self.header, packet, &TX_BUFFER_POOL);
+        let pool = TX_BUFFER_POOL.call_once(|| RingBuffer::new(TX_BUFFER_POOL_SIZE));
+        let tx_buffer = TxBuffer::new(&self.header, packet, pool);
 
         let token = self
             .send_queue
@@ -360,8 +364,7 @@ impl Debug for NetworkDevice {
     }
 }
 
-static TX_BUFFER_POOL: SpinLock<LinkedList<DmaStream>, BottomHalfDisabled> =
-    SpinLock::new(LinkedList::new());
+static TX_BUFFER_POOL: Once<RingBuffer<DmaStream>> = Once::new();
 
 const QUEUE_RECV: u16 = 0;
 const QUEUE_SEND: u16 = 1;
@@ -1,18 +1,17 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::linked_list::LinkedList, sync::Arc};
+use alloc::sync::Arc;
 
-use aster_network::dma_pool::DmaPool;
-use aster_softirq::BottomHalfDisabled;
+use aster_network::{dma_pool::DmaPool, TX_BUFFER_POOL_SIZE};
 use ostd::{
     mm::{DmaDirection, DmaStream},
-    sync::SpinLock,
+    sync::RingBuffer,
 };
 use spin::Once;
 
 const RX_BUFFER_LEN: usize = 4096;
 pub static RX_BUFFER_POOL: Once<Arc<DmaPool>> = Once::new();
-pub static TX_BUFFER_POOL: Once<SpinLock<LinkedList<DmaStream>, BottomHalfDisabled>> = Once::new();
+pub static TX_BUFFER_POOL: Once<RingBuffer<DmaStream>> = Once::new();
 
 pub fn init() {
     const POOL_INIT_SIZE: usize = 32;
@@ -26,5 +25,5 @@ pub fn init() {
             false,
         )
     });
-    TX_BUFFER_POOL.call_once(|| SpinLock::new(LinkedList::new()));
+    TX_BUFFER_POOL.call_once(|| RingBuffer::new(TX_BUFFER_POOL_SIZE));
 }
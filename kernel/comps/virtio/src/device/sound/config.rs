@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::offset_of;
+
+use aster_util::safe_ptr::SafePtr;
+use ostd::Pod;
+
+use crate::transport::{ConfigManager, VirtioTransport};
+
+bitflags::bitflags! {
+    pub struct SoundFeatures: u64 {
+        /// The device supports the control elements.
+        const VIRTIO_SND_F_CTLS = 1 << 0;
+    }
+}
+
+#[derive(Debug, Pod, Clone, Copy)]
+#[repr(C)]
+pub struct VirtioSoundConfig {
+    /// The number of the jacks.
+    pub jacks: u32,
+    /// The number of the PCM streams.
+    pub streams: u32,
+    /// The number of the channel maps.
+    pub chmaps: u32,
+}
+
+impl VirtioSoundConfig {
+    pub(super) fn new_manager(transport: &dyn VirtioTransport) -> ConfigManager<Self> {
+        let safe_ptr = transport
+            .device_config_mem()
+            .map(|mem| SafePtr::new(mem, 0));
+        let bar_space = transport.device_config_bar();
+        ConfigManager::new(safe_ptr, bar_space)
+    }
+}
+
+impl ConfigManager<VirtioSoundConfig> {
+    pub(super) fn read_config(&self) -> VirtioSoundConfig {
+        let mut config = VirtioSoundConfig::new_uninit();
+        config.jacks = self
+            .read_once::<u32>(offset_of!(VirtioSoundConfig, jacks))
+            .unwrap();
+        config.streams = self
+            .read_once::<u32>(offset_of!(VirtioSoundConfig, streams))
+            .unwrap();
+        config.chmaps = self
+            .read_once::<u32>(offset_of!(VirtioSoundConfig, chmaps))
+            .unwrap();
+
+        config
+    }
+}
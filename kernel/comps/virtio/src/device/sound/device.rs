@@ -0,0 +1,557 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::ToString,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::{fmt::Debug, hint::spin_loop, mem::size_of};
+
+use aster_sound::{PcmFormat, PcmInfo, PcmListener, PcmParams, PcmStream, SoundError};
+use log::{debug, info, warn};
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::SpinLock,
+    trap::TrapFrame,
+    Pod,
+};
+use spin::Once;
+
+use super::{
+    config::{SoundFeatures, VirtioSoundConfig},
+    header::{
+        RequestCode, StatusCode, VirtioPcmFormat, VirtioSndHdr, VirtioSndPcmHdr, VirtioSndPcmInfo,
+        VirtioSndPcmSetParams, VirtioSndPcmStatus, VirtioSndPcmXfer, VirtioSndQueryInfo, RATES,
+        VIRTIO_SND_D_OUTPUT,
+    },
+    DEVICE_NAME,
+};
+use crate::{
+    device::VirtioDeviceError,
+    queue::{QueueError, VirtQueue},
+    transport::{ConfigManager, VirtioTransport},
+};
+
+const CONTROL_QUEUE_INDEX: u16 = 0;
+const TX_QUEUE_INDEX: u16 = 2;
+const QUEUE_SIZE: u16 = 64;
+
+/// The maximum number of the PCM streams, whose information fits in the control buffer.
+const MAX_STREAMS: u32 = 32;
+/// The maximum number of the periods, so that a stream takes at most 3/4 of the transmit queue.
+const MAX_PERIODS: u32 = 16;
+const BUFFER_BYTES_MAX: usize = 128 * 1024;
+
+/// The offset of the responses in the control buffer, which follow the requests.
+const RESPONSE_OFFSET: usize = 64;
+
+/// The size of the slot of a period, which holds the header and the status of the period.
+const SLOT_SIZE: usize = 16;
+/// The offset of the status in the slot of a period.
+const STATUS_OFFSET: usize = 8;
+
+pub struct SoundDevice {
+    config_manager: ConfigManager<VirtioSoundConfig>,
+    transport: SpinLock<Box<dyn VirtioTransport>>,
+    control_queue: SpinLock<VirtQueue>,
+    /// The buffer of the control requests, which holds a request and its response.
+    control_buffer: DmaStream,
+    tx_queue: SpinLock<VirtQueue>,
+    /// The periods that are being played by the device, indexed by the tokens.
+    submitted_periods: SpinLock<BTreeMap<u16, SubmittedPeriod>>,
+    /// The playback streams, which are initialized after the device is ready.
+    streams: Once<Vec<Arc<PlaybackStream>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SubmittedPeriod {
+    /// The index of the stream in [`SoundDevice::streams`].
+    stream_index: usize,
+    period: usize,
+}
+
+impl SoundDevice {
+    pub(crate) fn negotiate_features(features: u64) -> u64 {
+        let mut features = SoundFeatures::from_bits_truncate(features);
+        // The control elements are not supported.
+        features.remove(SoundFeatures::VIRTIO_SND_F_CTLS);
+        features.bits()
+    }
+
+    pub(crate) fn init(mut transport: Box<dyn VirtioTransport>) -> Result<(), VirtioDeviceError> {
+        let config_manager = VirtioSoundConfig::new_manager(transport.as_ref());
+        let config = config_manager.read_config();
+        debug!("virtio_sound_config = {:?}", config);
+
+        // The event queue and the receive queue are not used, since jacks and capture streams
+        // are not supported.
+        let control_queue = VirtQueue::new(CONTROL_QUEUE_INDEX, QUEUE_SIZE, transport.as_mut())?;
+        let tx_queue = VirtQueue::new(TX_QUEUE_INDEX, QUEUE_SIZE, transport.as_mut())?;
+
+        let device = Arc::new(Self {
+            config_manager,
+            transport: SpinLock::new(transport),
+            control_queue: SpinLock::new(control_queue),
+            control_buffer: new_dma_stream(PAGE_SIZE, DmaDirection::Bidirectional),
+            tx_queue: SpinLock::new(tx_queue),
+            submitted_periods: SpinLock::new(BTreeMap::new()),
+            streams: Once::new(),
+        });
+
+        {
+            let mut transport = device.transport.disable_irq().lock();
+            let cloned_device = device.clone();
+            let handle_tx_irq = move |_: &TrapFrame| cloned_device.handle_tx_irq();
+            transport
+                .register_queue_callback(TX_QUEUE_INDEX, Box::new(handle_tx_irq), false)
+                .unwrap();
+            transport
+                .register_cfg_callback(Box::new(config_space_change))
+                .unwrap();
+            transport.finish_init();
+        }
+
+        let infos = match device.query_pcm_infos(config.streams.min(MAX_STREAMS)) {
+            Ok(infos) => infos,
+            Err(err) => {
+                warn!("[Virtio]: Failed to query the PCM streams: {:?}", err);
+                Vec::new()
+            }
+        };
+        let streams = device.streams.call_once(|| {
+            infos
+                .iter()
+                .enumerate()
+                .filter(|(_, info)| info.direction == VIRTIO_SND_D_OUTPUT)
+                .filter_map(|(id, info)| PlaybackStream::new(id as u32, info))
+                .enumerate()
+                .map(|(index, mut stream)| {
+                    stream.index = index;
+                    stream.device = Arc::downgrade(&device);
+                    Arc::new(stream)
+                })
+                .collect()
+        });
+
+        info!(
+            "[Virtio]: Found virtio-sound device with {} playback streams",
+            streams.len()
+        );
+        if !streams.is_empty() {
+            let pcms = streams
+                .iter()
+                .map(|stream| stream.clone() as Arc<dyn PcmStream>)
+                .collect();
+            aster_sound::register_card(DEVICE_NAME.to_string(), pcms);
+        }
+        Ok(())
+    }
+
+    /// Queries the information of the first `count` PCM streams.
+    fn query_pcm_infos(&self, count: u32) -> Result<Vec<VirtioSndPcmInfo>, SoundError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let info_len = size_of::<VirtioSndPcmInfo>();
+        let request = VirtioSndQueryInfo {
+            hdr: VirtioSndHdr {
+                code: RequestCode::PcmInfo as u32,
+            },
+            start_id: 0,
+            count,
+            size: info_len as u32,
+        };
+        let mut response = vec![0u8; size_of::<VirtioSndHdr>() + info_len * count as usize];
+        self.request(request.as_bytes(), &mut response)?;
+
+        Ok(response[size_of::<VirtioSndHdr>()..]
+            .chunks_exact(info_len)
+            .map(VirtioSndPcmInfo::from_bytes)
+            .collect())
+    }
+
+    /// Sends a request that only has the PCM header to the device.
+    fn pcm_request(&self, code: RequestCode, stream_id: u32) -> Result<(), SoundError> {
+        let request = VirtioSndPcmHdr {
+            hdr: VirtioSndHdr { code: code as u32 },
+            stream_id,
+        };
+        self.request(
+            request.as_bytes(),
+            VirtioSndHdr::new_zeroed().as_bytes_mut(),
+        )
+    }
+
+    /// Sends a control request and waits for the response.
+    ///
+    /// The response starts with a [`VirtioSndHdr`], whose status code is checked.
+    fn request(&self, request: &[u8], response: &mut [u8]) -> Result<(), SoundError> {
+        let mut control_queue = self.control_queue.disable_irq().lock();
+
+        self.control_buffer.write_bytes(0, request).unwrap();
+        self.control_buffer.sync(0..request.len()).unwrap();
+        let request_slice = DmaStreamSlice::new(&self.control_buffer, 0, request.len());
+        let response_slice =
+            DmaStreamSlice::new(&self.control_buffer, RESPONSE_OFFSET, response.len());
+
+        control_queue
+            .add_dma_buf(&[&request_slice], &[&response_slice])
+            .map_err(|_| SoundError::Io)?;
+        if control_queue.should_notify() {
+            control_queue.notify();
+        }
+        while !control_queue.can_pop() {
+            spin_loop();
+        }
+        control_queue.pop_used().unwrap();
+
+        response_slice.sync().unwrap();
+        self.control_buffer
+            .read_bytes(RESPONSE_OFFSET, response)
+            .unwrap();
+        let hdr = VirtioSndHdr::from_bytes(&response[..size_of::<VirtioSndHdr>()]);
+        StatusCode::check(hdr.code)
+    }
+
+    /// Submits a period of the buffer to the transmit queue.
+    fn submit_period(
+        &self,
+        stream_index: usize,
+        buffer: &PreparedBuffer,
+        period: usize,
+    ) -> Result<(), QueueError> {
+        let offset = period * buffer.period_bytes;
+        buffer
+            .data
+            .sync(offset..offset + buffer.period_bytes)
+            .unwrap();
+
+        let slot = period * SLOT_SIZE;
+        let header = DmaStreamSlice::new(&buffer.slots, slot, size_of::<VirtioSndPcmXfer>());
+        let data = DmaStreamSlice::new(&buffer.data, offset, buffer.period_bytes);
+        let status = DmaStreamSlice::new(
+            &buffer.slots,
+            slot + STATUS_OFFSET,
+            size_of::<VirtioSndPcmStatus>(),
+        );
+
+        // The period is recorded with the queue locked, so the interrupt handler can find it.
+        let mut tx_queue = self.tx_queue.disable_irq().lock();
+        let token = tx_queue.add_dma_buf(&[&header, &data], &[&status])?;
+        self.submitted_periods.disable_irq().lock().insert(
+            token,
+            SubmittedPeriod {
+                stream_index,
+                period,
+            },
+        );
+
+        if tx_queue.should_notify() {
+            tx_queue.notify();
+        }
+        Ok(())
+    }
+
+    /// Returns the number of the periods of the stream that are being played by the device.
+    fn num_submitted(&self, stream_index: usize) -> usize {
+        self.submitted_periods
+            .disable_irq()
+            .lock()
+            .values()
+            .filter(|submitted| submitted.stream_index == stream_index)
+            .count()
+    }
+
+    fn handle_tx_irq(&self) {
+        // When we enter the IRQs handling function, IRQs have already been disabled, so there
+        // is no need to call `disable_irq`.
+        loop {
+            let submitted = {
+                let mut tx_queue = self.tx_queue.lock();
+                let Ok((token, _)) = tx_queue.pop_used() else {
+                    break;
+                };
+                self.submitted_periods.lock().remove(&token).unwrap()
+            };
+
+            let Some(stream) = self
+                .streams
+                .get()
+                .and_then(|streams| streams.get(submitted.stream_index))
+            else {
+                continue;
+            };
+            stream.handle_period_done(self, submitted.period);
+        }
+    }
+}
+
+impl Debug for SoundDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SoundDevice")
+            .field("config", &self.config_manager.read_config())
+            .field("transport", &self.transport)
+            .field("control_queue", &self.control_queue)
+            .field("tx_queue", &self.tx_queue)
+            .finish()
+    }
+}
+
+/// A playback PCM stream of a virtio sound device.
+pub struct PlaybackStream {
+    /// The ID of the stream in the device.
+    id: u32,
+    /// The index of the stream in [`SoundDevice::streams`].
+    index: usize,
+    info: PcmInfo,
+    device: Weak<SoundDevice>,
+    state: SpinLock<StreamState>,
+    listener: SpinLock<Option<Weak<dyn PcmListener>>>,
+}
+
+#[derive(Default)]
+struct StreamState {
+    /// The buffer that is set by the last preparation.
+    ///
+    /// It is `None` if the stream is not prepared, or it has been stopped.
+    prepared: Option<PreparedBuffer>,
+    is_running: bool,
+}
+
+struct PreparedBuffer {
+    data: DmaStream,
+    period_bytes: usize,
+    periods: usize,
+    /// The slots of the periods, each of which has a size of [`SLOT_SIZE`].
+    slots: DmaStream,
+}
+
+impl PlaybackStream {
+    fn new(id: u32, info: &VirtioSndPcmInfo) -> Option<Self> {
+        let formats: Vec<PcmFormat> = PcmFormat::ALL
+            .into_iter()
+            .filter(|format| info.formats & (1 << VirtioPcmFormat::from(*format) as u8) != 0)
+            .collect();
+        let rates: Vec<u32> = RATES
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| info.rates & (1 << index) != 0)
+            .map(|(_, rate)| *rate)
+            .collect();
+        let channels_min = info.channels_min.max(1) as u32;
+        let channels_max = info.channels_max as u32;
+        if formats.is_empty() || rates.is_empty() || channels_min > channels_max {
+            warn!("[Virtio]: PCM stream {} has no usable configuration", id);
+            return None;
+        }
+
+        Some(Self {
+            id,
+            index: 0,
+            info: PcmInfo {
+                formats,
+                rates,
+                channels: channels_min..=channels_max,
+                periods: 2..=MAX_PERIODS,
+                buffer_bytes_max: BUFFER_BYTES_MAX,
+            },
+            device: Weak::new(),
+            state: SpinLock::new(StreamState::default()),
+            listener: SpinLock::new(None),
+        })
+    }
+
+    fn device(&self) -> Result<Arc<SoundDevice>, SoundError> {
+        self.device.upgrade().ok_or(SoundError::Io)
+    }
+
+    /// Handles a period that is returned by the device, and submits it again if the listener
+    /// wants the stream to keep running.
+    fn handle_period_done(&self, device: &SoundDevice, period: usize) {
+        {
+            let state = self.state.lock();
+            let Some(prepared) = state.prepared.as_ref() else {
+                return;
+            };
+            if !state.is_running {
+                return;
+            }
+
+            let offset = period * SLOT_SIZE + STATUS_OFFSET;
+            prepared
+                .slots
+                .sync(offset..offset + size_of::<VirtioSndPcmStatus>())
+                .unwrap();
+            let status: VirtioSndPcmStatus = prepared.slots.read_val(offset).unwrap();
+            if let Err(err) = StatusCode::check(status.status) {
+                warn!(
+                    "[Virtio]: PCM stream {} failed to play a period: {:?}",
+                    self.id, err
+                );
+            }
+        }
+
+        let listener = self.listener.lock().as_ref().and_then(Weak::upgrade);
+        if !listener.is_some_and(|listener| listener.on_period_elapsed()) {
+            return;
+        }
+
+        let state = self.state.lock();
+        if !state.is_running {
+            return;
+        }
+        let Some(prepared) = state.prepared.as_ref() else {
+            return;
+        };
+        if let Err(err) = device.submit_period(self.index, prepared, period) {
+            warn!(
+                "[Virtio]: PCM stream {} failed to submit a period: {:?}",
+                self.id, err
+            );
+        }
+    }
+}
+
+impl PcmStream for PlaybackStream {
+    fn info(&self) -> &PcmInfo {
+        &self.info
+    }
+
+    fn prepare(&self, params: &PcmParams, buffer: &DmaStream) -> Result<(), SoundError> {
+        self.stop()?;
+
+        let periods = params.periods();
+        if params.period_bytes == 0
+            || params.buffer_bytes != params.period_bytes * periods
+            || !self.info.periods.contains(&(periods as u32))
+            || params.buffer_bytes > self.info.buffer_bytes_max
+            || buffer.nbytes() < params.buffer_bytes
+        {
+            return Err(SoundError::InvalidArgs);
+        }
+        if !self.info.formats.contains(&params.format)
+            || !self.info.channels.contains(&params.channels)
+        {
+            return Err(SoundError::NotSupported);
+        }
+        let rate = RATES
+            .iter()
+            .position(|rate| *rate == params.rate)
+            .ok_or(SoundError::NotSupported)?;
+
+        let device = self.device()?;
+        let request = VirtioSndPcmSetParams {
+            hdr: VirtioSndPcmHdr {
+                hdr: VirtioSndHdr {
+                    code: RequestCode::PcmSetParams as u32,
+                },
+                stream_id: self.id,
+            },
+            buffer_bytes: params.buffer_bytes as u32,
+            period_bytes: params.period_bytes as u32,
+            features: 0,
+            channels: params.channels as u8,
+            format: VirtioPcmFormat::from(params.format) as u8,
+            rate: rate as u8,
+            padding: 0,
+        };
+        device.request(
+            request.as_bytes(),
+            VirtioSndHdr::new_zeroed().as_bytes_mut(),
+        )?;
+        device.pcm_request(RequestCode::PcmPrepare, self.id)?;
+
+        let slots = new_dma_stream(periods * SLOT_SIZE, DmaDirection::Bidirectional);
+        let header = VirtioSndPcmXfer { stream_id: self.id };
+        for period in 0..periods {
+            slots.write_val(period * SLOT_SIZE, &header).unwrap();
+        }
+        slots.sync(0..periods * SLOT_SIZE).unwrap();
+
+        self.state.disable_irq().lock().prepared = Some(PreparedBuffer {
+            data: buffer.clone(),
+            period_bytes: params.period_bytes,
+            periods,
+            slots,
+        });
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), SoundError> {
+        let device = self.device()?;
+
+        {
+            let mut state = self.state.disable_irq().lock();
+            if state.is_running {
+                return Err(SoundError::InvalidArgs);
+            }
+            let Some(prepared) = state.prepared.as_ref() else {
+                return Err(SoundError::InvalidArgs);
+            };
+            for period in 0..prepared.periods {
+                device
+                    .submit_period(self.index, prepared, period)
+                    .map_err(|_| SoundError::Io)?;
+            }
+            state.is_running = true;
+        }
+
+        device.pcm_request(RequestCode::PcmStart, self.id)
+    }
+
+    fn stop(&self) -> Result<(), SoundError> {
+        let was_running = {
+            let mut state = self.state.disable_irq().lock();
+            if state.prepared.is_none() {
+                return Ok(());
+            }
+            core::mem::replace(&mut state.is_running, false)
+        };
+
+        let device = self.device()?;
+        let stop_result = if was_running {
+            device.pcm_request(RequestCode::PcmStop, self.id)
+        } else {
+            Ok(())
+        };
+        let release_result = device.pcm_request(RequestCode::PcmRelease, self.id);
+
+        // The device returns all the pending periods when the stream is released. After that,
+        // the buffer is no longer accessed by the device.
+        while device.num_submitted(self.index) > 0 {
+            spin_loop();
+        }
+        self.state.disable_irq().lock().prepared = None;
+
+        stop_result.and(release_result)
+    }
+
+    fn set_listener(&self, listener: Weak<dyn PcmListener>) {
+        *self.listener.disable_irq().lock() = Some(listener);
+    }
+}
+
+impl Debug for PlaybackStream {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PlaybackStream")
+            .field("id", &self.id)
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+fn new_dma_stream(len: usize, direction: DmaDirection) -> DmaStream {
+    let segment = FrameAllocOptions::new()
+        .zeroed(false)
+        .alloc_segment(len.div_ceil(PAGE_SIZE))
+        .unwrap();
+    DmaStream::map(segment.into(), direction, false).unwrap()
+}
+
+fn config_space_change(_: &TrapFrame) {
+    debug!("Virtio-Sound device configuration space change");
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_sound::{PcmFormat, SoundError};
+use int_to_c_enum::TryFromInt;
+use ostd::Pod;
+
+/// The codes of the control requests.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RequestCode {
+    PcmInfo = 0x0100,
+    PcmSetParams = 0x0101,
+    PcmPrepare = 0x0102,
+    PcmRelease = 0x0103,
+    PcmStart = 0x0104,
+    PcmStop = 0x0105,
+}
+
+/// The status codes of the responses.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub(super) enum StatusCode {
+    Ok = 0x8000,
+    BadMsg = 0x8001,
+    NotSupp = 0x8002,
+    IoErr = 0x8003,
+}
+
+impl StatusCode {
+    /// Converts the raw status code to a result.
+    pub(super) fn check(code: u32) -> Result<(), SoundError> {
+        match StatusCode::try_from(code) {
+            Ok(StatusCode::Ok) => Ok(()),
+            Ok(StatusCode::BadMsg) => Err(SoundError::InvalidArgs),
+            Ok(StatusCode::NotSupp) => Err(SoundError::NotSupported),
+            Ok(StatusCode::IoErr) | Err(_) => Err(SoundError::Io),
+        }
+    }
+}
+
+/// The header of the requests and the responses.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndHdr {
+    pub code: u32,
+}
+
+/// The request to query the information of the items (e.g., the PCM streams).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndQueryInfo {
+    pub hdr: VirtioSndHdr,
+    pub start_id: u32,
+    pub count: u32,
+    pub size: u32,
+}
+
+/// The information of a PCM stream.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndPcmInfo {
+    pub hda_fn_nid: u32,
+    pub features: u32,
+    /// The bitmap of the supported formats, indexed by [`VirtioPcmFormat`].
+    pub formats: u64,
+    /// The bitmap of the supported rates, indexed by the positions in [`RATES`].
+    pub rates: u64,
+    pub direction: u8,
+    pub channels_min: u8,
+    pub channels_max: u8,
+    pub padding: [u8; 5],
+}
+
+/// The direction of the PCM stream that plays the audio.
+pub(super) const VIRTIO_SND_D_OUTPUT: u8 = 0;
+
+/// The header of the requests to a PCM stream.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndPcmHdr {
+    pub hdr: VirtioSndHdr,
+    pub stream_id: u32,
+}
+
+/// The request to set the parameters of a PCM stream.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndPcmSetParams {
+    pub hdr: VirtioSndPcmHdr,
+    pub buffer_bytes: u32,
+    pub period_bytes: u32,
+    pub features: u32,
+    pub channels: u8,
+    pub format: u8,
+    pub rate: u8,
+    pub padding: u8,
+}
+
+/// The header of the buffers in the transmit queue.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndPcmXfer {
+    pub stream_id: u32,
+}
+
+/// The status of the buffers in the transmit queue, which is written by the device.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct VirtioSndPcmStatus {
+    pub status: u32,
+    pub latency_bytes: u32,
+}
+
+/// The formats of the samples in the virtio sound specification.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum VirtioPcmFormat {
+    S8 = 3,
+    U8 = 4,
+    S16 = 5,
+    U16 = 6,
+    S24 = 15,
+    U24 = 16,
+    S32 = 17,
+    U32 = 18,
+    Float = 19,
+}
+
+impl From<PcmFormat> for VirtioPcmFormat {
+    fn from(format: PcmFormat) -> Self {
+        match format {
+            PcmFormat::S8 => Self::S8,
+            PcmFormat::U8 => Self::U8,
+            PcmFormat::S16Le => Self::S16,
+            PcmFormat::U16Le => Self::U16,
+            PcmFormat::S24Le => Self::S24,
+            PcmFormat::U24Le => Self::U24,
+            PcmFormat::S32Le => Self::S32,
+            PcmFormat::U32Le => Self::U32,
+            PcmFormat::FloatLe => Self::Float,
+        }
+    }
+}
+
+/// The rates in Hz, indexed by the rate codes in the virtio sound specification.
+pub(super) const RATES: [u32; 14] = [
+    5512, 8000, 11025, 16000, 22050, 32000, 44100, 48000, 64000, 88200, 96000, 176400, 192000,
+    384000,
+];
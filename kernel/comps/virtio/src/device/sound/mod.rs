@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The virtio sound device.
+//!
+//! Only the playback (output) PCM streams are supported. They are registered as a sound card
+//! of the sound subsystem, and each period of the buffer is sent to the device as a buffer of
+//! the transmit queue.
+
+pub mod config;
+pub mod device;
+mod header;
+
+pub static DEVICE_NAME: &str = "Virtio-Sound";
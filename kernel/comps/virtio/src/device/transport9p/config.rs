@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::string::String;
+use core::mem::offset_of;
+
+use aster_util::safe_ptr::SafePtr;
+use ostd::Pod;
+
+use crate::transport::{ConfigManager, VirtioTransport};
+
+bitflags::bitflags! {
+    pub struct Transport9PFeatures: u64 {
+        /// The mount tag is in the configuration space.
+        const VIRTIO_9P_MOUNT_TAG = 1 << 0;
+    }
+}
+
+/// The maximum length of the tag.
+pub const MAX_TAG_LEN: usize = 255;
+
+#[derive(Debug, Pod, Clone, Copy)]
+#[repr(C)]
+pub struct Virtio9PConfig {
+    /// The length of the tag.
+    pub tag_len: u16,
+    /// The name of the file system, which is encoded in UTF-8 and is not NUL-terminated.
+    ///
+    /// Only the first `tag_len` bytes exist in the configuration space.
+    pub tag: [u8; MAX_TAG_LEN],
+}
+
+impl Virtio9PConfig {
+    pub(super) fn new_manager(transport: &dyn VirtioTransport) -> ConfigManager<Self> {
+        let safe_ptr = transport
+            .device_config_mem()
+            .map(|mem| SafePtr::new(mem, 0));
+        let bar_space = transport.device_config_bar();
+        ConfigManager::new(safe_ptr, bar_space)
+    }
+}
+
+impl ConfigManager<Virtio9PConfig> {
+    pub(super) fn read_config(&self) -> Virtio9PConfig {
+        let mut config = Virtio9PConfig::new_zeroed();
+        config.tag_len = self
+            .read_once::<u16>(offset_of!(Virtio9PConfig, tag_len))
+            .unwrap();
+
+        let tag_len = (config.tag_len as usize).min(MAX_TAG_LEN);
+        for (index, byte) in config.tag[..tag_len].iter_mut().enumerate() {
+            *byte = self
+                .read_once::<u8>(offset_of!(Virtio9PConfig, tag) + index)
+                .unwrap();
+        }
+
+        config
+    }
+
+    /// Reads the tag, which is used as the source of the mount.
+    pub(super) fn read_tag(&self) -> String {
+        let config = self.read_config();
+        let tag_len = (config.tag_len as usize).min(MAX_TAG_LEN);
+        String::from_utf8_lossy(&config.tag[..tag_len]).into_owned()
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Debug;
+
+use log::{debug, info, warn};
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::SpinLock,
+    trap::TrapFrame,
+};
+
+use super::{
+    config::{Transport9PFeatures, Virtio9PConfig},
+    register_device,
+};
+use crate::{
+    device::VirtioDeviceError,
+    queue::{QueueError, VirtQueue},
+    transport::{ConfigManager, VirtioTransport},
+};
+
+/// The callback that is called in the interrupt context when requests are completed.
+pub type ReplyCallback = dyn Fn() + Send + Sync;
+
+pub struct Transport9PDevice {
+    tag: String,
+    config_manager: ConfigManager<Virtio9PConfig>,
+    transport: SpinLock<Box<dyn VirtioTransport>>,
+    request_queue: SpinLock<VirtQueue>,
+    /// The requests that are being processed by the device, indexed by the tokens.
+    submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>>,
+    /// The replies that have not been taken.
+    replies: SpinLock<VecDeque<Vec<u8>>>,
+    callback: SpinLock<Option<Box<ReplyCallback>>>,
+}
+
+struct SubmittedRequest {
+    /// The buffer of the request, which is kept until the device has read it.
+    _request: DmaStream,
+    reply: DmaStream,
+}
+
+impl Transport9PDevice {
+    const REQUEST_QUEUE_INDEX: u16 = 0;
+    const QUEUE_SIZE: u16 = 64;
+
+    pub(crate) fn negotiate_features(features: u64) -> u64 {
+        let features = Transport9PFeatures::from_bits_truncate(features);
+        features.bits()
+    }
+
+    pub(crate) fn init(mut transport: Box<dyn VirtioTransport>) -> Result<(), VirtioDeviceError> {
+        let config_manager = Virtio9PConfig::new_manager(transport.as_ref());
+        debug!("virtio_9p_config = {:?}", config_manager.read_config());
+
+        let request_queue = VirtQueue::new(
+            Self::REQUEST_QUEUE_INDEX,
+            Self::QUEUE_SIZE,
+            transport.as_mut(),
+        )?;
+
+        let tag = config_manager.read_tag();
+        let device = Arc::new(Self {
+            tag: tag.clone(),
+            config_manager,
+            transport: SpinLock::new(transport),
+            request_queue: SpinLock::new(request_queue),
+            submitted_requests: SpinLock::new(BTreeMap::new()),
+            replies: SpinLock::new(VecDeque::new()),
+            callback: SpinLock::new(None),
+        });
+
+        {
+            let mut transport = device.transport.disable_irq().lock();
+            let cloned_device = device.clone();
+            let handle_irq = move |_: &TrapFrame| cloned_device.handle_irq();
+            transport
+                .register_queue_callback(Self::REQUEST_QUEUE_INDEX, Box::new(handle_irq), false)
+                .unwrap();
+            transport
+                .register_cfg_callback(Box::new(config_space_change))
+                .unwrap();
+            transport.finish_init();
+        }
+
+        info!("[Virtio]: Found virtio-9p device with tag {:?}", tag);
+        register_device(tag, device);
+        Ok(())
+    }
+
+    /// Returns the tag, which names the file system.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Submits a request to the device.
+    ///
+    /// The device replies with at most `reply_len` bytes, which can be taken with
+    /// [`Self::pop_reply`] when the callback is called.
+    ///
+    /// If there are not enough descriptors, [`QueueError::BufferTooSmall`] is returned and the
+    /// request should be submitted again after some requests are completed.
+    pub fn submit(&self, request: &[u8], reply_len: usize) -> Result<(), QueueError> {
+        let request_stream = new_dma_stream(request.len(), DmaDirection::ToDevice);
+        request_stream.write_bytes(0, request).unwrap();
+        request_stream.sync(0..request.len()).unwrap();
+        let request_slice = DmaStreamSlice::new(&request_stream, 0, request.len());
+
+        let reply_stream = new_dma_stream(reply_len, DmaDirection::FromDevice);
+        let reply_slice = DmaStreamSlice::new(&reply_stream, 0, reply_len);
+
+        // The request is recorded with the queue locked, so the interrupt handler can find it.
+        let mut queue = self.request_queue.disable_irq().lock();
+        let token = queue.add_dma_buf(&[&request_slice], &[&reply_slice])?;
+        self.submitted_requests.disable_irq().lock().insert(
+            token,
+            SubmittedRequest {
+                _request: request_stream.clone(),
+                reply: reply_stream.clone(),
+            },
+        );
+
+        if queue.should_notify() {
+            queue.notify();
+        }
+        Ok(())
+    }
+
+    /// Takes a reply that is written by the device.
+    pub fn pop_reply(&self) -> Option<Vec<u8>> {
+        self.replies.disable_irq().lock().pop_front()
+    }
+
+    /// Returns the number of the requests that are being processed by the device.
+    pub fn num_submitted(&self) -> usize {
+        self.submitted_requests.disable_irq().lock().len()
+    }
+
+    /// Sets the callback that is called when requests are completed.
+    pub fn set_callback(&self, callback: Box<ReplyCallback>) {
+        *self.callback.disable_irq().lock() = Some(callback);
+    }
+
+    fn handle_irq(&self) {
+        // When we enter the IRQs handling function, IRQs have already been disabled, so there
+        // is no need to call `disable_irq`.
+        loop {
+            let (request, len) = {
+                let mut queue = self.request_queue.lock();
+                let Ok((token, len)) = queue.pop_used() else {
+                    break;
+                };
+                let request = self.submitted_requests.lock().remove(&token).unwrap();
+                (request, len as usize)
+            };
+
+            let reply = request.reply;
+            let len = len.min(reply.nbytes());
+            if len == 0 {
+                warn!("virtio-9p device {:?} replies with nothing", self.tag);
+                continue;
+            }
+            reply.sync(0..len).unwrap();
+            let mut bytes = vec![0; len];
+            reply.read_bytes(0, &mut bytes).unwrap();
+            self.replies.lock().push_back(bytes);
+        }
+
+        if let Some(callback) = self.callback.lock().as_ref() {
+            callback();
+        }
+    }
+}
+
+impl Debug for Transport9PDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Transport9PDevice")
+            .field("tag", &self.tag)
+            .field("config", &self.config_manager.read_config())
+            .field("transport", &self.transport)
+            .field("request_queue", &self.request_queue)
+            .finish()
+    }
+}
+
+fn new_dma_stream(len: usize, direction: DmaDirection) -> DmaStream {
+    let segment = FrameAllocOptions::new()
+        .zeroed(false)
+        .alloc_segment(len.div_ceil(PAGE_SIZE))
+        .unwrap();
+    DmaStream::map(segment.into(), direction, false).unwrap()
+}
+
+fn config_space_change(_: &TrapFrame) {
+    debug!("Virtio-9P device configuration space change");
+}
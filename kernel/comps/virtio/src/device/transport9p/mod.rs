@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The virtio 9P transport device, which carries 9P messages over a virtqueue.
+//!
+//! The device is found by its tag, which is used as the source when the file system is mounted.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+
+use ostd::sync::SpinLock;
+
+use self::device::Transport9PDevice;
+
+pub mod config;
+pub mod device;
+
+pub static DEVICE_NAME: &str = "Virtio-9P";
+
+static DEVICE_TABLE: SpinLock<BTreeMap<String, Arc<Transport9PDevice>>> =
+    SpinLock::new(BTreeMap::new());
+
+pub fn register_device(tag: String, device: Arc<Transport9PDevice>) {
+    DEVICE_TABLE.disable_irq().lock().insert(tag, device);
+}
+
+pub fn get_device(tag: &str) -> Option<Arc<Transport9PDevice>> {
+    DEVICE_TABLE.disable_irq().lock().get(tag).cloned()
+}
@@ -9,17 +9,21 @@
 
 extern crate alloc;
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, format, vec};
 use core::hint::spin_loop;
 
+use aster_systree::{pci_device, KObjectBuilder};
 use bitflags::bitflags;
 use component::{init_component, ComponentInitError};
 use device::{
     block::device::BlockDevice,
     console::device::ConsoleDevice,
+    filesystem::device::FileSystemDevice,
     input::device::InputDevice,
     network::device::NetworkDevice,
     socket::{self, device::SocketDevice},
+    sound::device::SoundDevice,
+    transport9p::device::Transport9PDevice,
     VirtioDeviceType,
 };
 use log::{error, warn};
@@ -38,6 +42,7 @@ fn virtio_component_init() -> Result<(), ComponentInitError> {
     transport::init();
     // For vsock table static init
     socket::init();
+    let mut index = 0;
     while let Some(mut transport) = pop_device_transport() {
         // Reset device
         transport
@@ -62,12 +67,23 @@ fn virtio_component_init() -> Result<(), ComponentInitError> {
         }
 
         let device_type = transport.device_type();
+        if let Err(err) = add_to_device_model(index, transport.as_ref()) {
+            warn!(
+                "[Virtio]: Failed to add the device to the device model: {:?}",
+                err
+            );
+        }
+        index += 1;
+
         let res = match transport.device_type() {
             VirtioDeviceType::Block => BlockDevice::init(transport),
             VirtioDeviceType::Input => InputDevice::init(transport),
             VirtioDeviceType::Network => NetworkDevice::init(transport),
             VirtioDeviceType::Console => ConsoleDevice::init(transport),
             VirtioDeviceType::Socket => SocketDevice::init(transport),
+            VirtioDeviceType::Sound => SoundDevice::init(transport),
+            VirtioDeviceType::FileSystem => FileSystemDevice::init(transport),
+            VirtioDeviceType::Transport9P => Transport9PDevice::init(transport),
             _ => {
                 warn!("[Virtio]: Found unimplemented device:{:?}", device_type);
                 Ok(())
@@ -83,6 +99,35 @@ fn virtio_component_init() -> Result<(), ComponentInitError> {
     Ok(())
 }
 
+/// The name of the virtio bus in the device model.
+const VIRTIO_BUS_NAME: &str = "virtio";
+
+/// Adds a virtio device to the device model.
+///
+/// The device is placed under the PCI device that carries it,
+/// or under the platform devices if it is an MMIO device.
+fn add_to_device_model(index: usize, transport: &dyn VirtioTransport) -> aster_systree::Result<()> {
+    let device_model = aster_systree::device_model();
+    device_model.register_bus(VIRTIO_BUS_NAME)?;
+
+    let parent = transport
+        .pci_location()
+        .and_then(|location| pci_device(&location))
+        .unwrap_or_else(|| device_model.platform().clone());
+    let device_id = transport.device_type() as u32;
+    let vendor_id = transport.vendor_id();
+    let modalias = format!("virtio:d{:08X}v{:08X}", device_id, vendor_id);
+
+    let mut builder = KObjectBuilder::new(format!("virtio{}", index).into());
+    builder
+        .attr("device", format!("{:#06x}", device_id))
+        .attr("vendor", format!("{:#06x}", vendor_id))
+        .attr("modalias", modalias.clone())
+        .uevent(vec![("MODALIAS", modalias)]);
+    let device = builder.build(parent.as_ref())?;
+    device_model.add_device(&parent, device, Some(VIRTIO_BUS_NAME), None)
+}
+
 fn pop_device_transport() -> Option<Box<dyn VirtioTransport>> {
     if let Some(device) = VIRTIO_PCI_DRIVER.get().unwrap().pop_device_transport() {
         return Some(device);
@@ -103,6 +148,13 @@ fn negotiate_features(transport: &mut Box<dyn VirtioTransport>) {
         VirtioDeviceType::Input => InputDevice::negotiate_features(device_specified_features),
         VirtioDeviceType::Console => ConsoleDevice::negotiate_features(device_specified_features),
         VirtioDeviceType::Socket => SocketDevice::negotiate_features(device_specified_features),
+        VirtioDeviceType::Sound => SoundDevice::negotiate_features(device_specified_features),
+        VirtioDeviceType::FileSystem => {
+            FileSystemDevice::negotiate_features(device_specified_features)
+        }
+        VirtioDeviceType::Transport9P => {
+            Transport9PDevice::negotiate_features(device_specified_features)
+        }
         _ => device_specified_features,
     };
     let mut support_feature = Feature::from_bits_truncate(features);
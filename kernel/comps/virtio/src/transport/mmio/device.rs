@@ -93,6 +93,12 @@ impl VirtioTransport for VirtioMmioTransport {
         VirtioDeviceType::try_from(self.device.device_id() as u8).unwrap()
     }
 
+    fn vendor_id(&self) -> u32 {
+        field_ptr!(&self.layout, VirtioMmioLayout, vendor_id)
+            .read_once()
+            .unwrap()
+    }
+
     fn set_queue(
         &mut self,
         idx: u16,
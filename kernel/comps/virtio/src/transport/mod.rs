@@ -6,7 +6,7 @@ use core::fmt::Debug;
 use aster_util::safe_ptr::SafePtr;
 use ostd::{
     arch::device::io_port::{PortRead, PortWrite},
-    bus::pci::cfg_space::Bar,
+    bus::pci::{cfg_space::Bar, PciDeviceLocation},
     io::IoMem,
     mm::{DmaCoherent, PodOnce},
     trap::IrqCallbackFunction,
@@ -34,6 +34,14 @@ pub trait VirtioTransport: Sync + Send + Debug {
     /// Get device type.
     fn device_type(&self) -> VirtioDeviceType;
 
+    /// Get the vendor ID of the device.
+    fn vendor_id(&self) -> u32;
+
+    /// Get the location of the device on the PCI bus, if it is a PCI device.
+    fn pci_location(&self) -> Option<PciDeviceLocation> {
+        None
+    }
+
     /// Get device features.
     fn read_device_features(&self) -> u64;
 
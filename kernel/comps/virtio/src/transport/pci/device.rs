@@ -9,7 +9,7 @@ use ostd::{
     bus::{
         pci::{
             bus::PciDevice, capability::CapabilityData, cfg_space::Bar,
-            common_device::PciCommonDevice, PciDeviceId,
+            common_device::PciCommonDevice, PciDeviceId, PciDeviceLocation,
         },
         BusProbeError,
     },
@@ -73,6 +73,14 @@ impl VirtioTransport for VirtioPciModernTransport {
         self.device_type
     }
 
+    fn vendor_id(&self) -> u32 {
+        self.common_device.device_id().subsystem_vendor_id as u32
+    }
+
+    fn pci_location(&self) -> Option<PciDeviceLocation> {
+        Some(*self.common_device.location())
+    }
+
     fn set_queue(
         &mut self,
         idx: u16,
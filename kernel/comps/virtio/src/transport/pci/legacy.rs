@@ -7,7 +7,10 @@ use aster_util::safe_ptr::SafePtr;
 use log::{info, warn};
 use ostd::{
     bus::{
-        pci::{capability::CapabilityData, cfg_space::Bar, common_device::PciCommonDevice},
+        pci::{
+            capability::CapabilityData, cfg_space::Bar, common_device::PciCommonDevice,
+            PciDeviceLocation,
+        },
         BusProbeError,
     },
     io::IoMem,
@@ -159,6 +162,14 @@ impl VirtioTransport for VirtioPciLegacyTransport {
         self.device_type
     }
 
+    fn vendor_id(&self) -> u32 {
+        self.common_device.device_id().subsystem_vendor_id as u32
+    }
+
+    fn pci_location(&self) -> Option<PciDeviceLocation> {
+        Some(*self.common_device.location())
+    }
+
     fn set_queue(
         &mut self,
         idx: u16,
@@ -2,7 +2,11 @@
 
 use alloc::vec::Vec;
 
-use ostd::{bus::pci::capability::msix::CapabilityMsixData, trap::IrqLine};
+use ostd::{
+    bus::pci::capability::msix::CapabilityMsixData,
+    cpu::{num_cpus, CpuId, CpuSet},
+    trap::IrqLine,
+};
 
 pub struct VirtioMsixManager {
     config_msix_vector: u16,
@@ -57,8 +61,13 @@ impl VirtioMsixManager {
     /// Pop unused vector. If a virtqueue will send interrupt frequently.
     /// Then this virtqueue should use the single IRQ that this function provides.
     /// this function will return the MSI-X vector and corresponding IRQ.
+    ///
+    /// The vectors are hinted to be handled by different CPUs in turn,
+    /// so that the interrupts of the virtqueues are spread across the CPUs.
     pub fn pop_unused_irq(&mut self) -> Option<(u16, &mut IrqLine)> {
         let vector = self.unused_msix_vectors.pop()?;
+        let cpu = CpuId::try_from(self.used_msix_vectors.len() % num_cpus()).unwrap();
+        self.msix.set_affinity_hint(vector, &CpuSet::from(cpu));
         self.used_msix_vectors.push(vector);
         Some((vector, self.msix.irq_mut(vector as usize).unwrap()))
     }
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of watchdogs and the watchdog core.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use log::{error, info};
+use ostd::sync::Mutex;
+
+use crate::{Watchdog, WatchdogError, WatchdogOptions};
+
+/// A registered watchdog.
+#[derive(Debug)]
+pub struct WatchdogDevice {
+    index: u32,
+    watchdog: Arc<dyn Watchdog>,
+    state: Mutex<DeviceState>,
+}
+
+#[derive(Debug)]
+struct DeviceState {
+    is_opened: bool,
+    is_running: bool,
+    /// The timeout in seconds.
+    timeout: u32,
+    /// Whether the magic character has been written since the last write.
+    allows_release: bool,
+}
+
+impl WatchdogDevice {
+    /// Returns the index of the device, which is `N` in `watchdogN`.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the name of the device.
+    pub fn name(&self) -> String {
+        alloc::format!("watchdog{}", self.index)
+    }
+
+    pub fn watchdog(&self) -> &Arc<dyn Watchdog> {
+        &self.watchdog
+    }
+
+    /// Opens the device exclusively and starts the watchdog.
+    pub fn open(self: &Arc<Self>) -> Result<WatchdogHandle, WatchdogError> {
+        let mut state = self.state.lock();
+        if state.is_opened {
+            return Err(WatchdogError::Busy);
+        }
+
+        self.start(&mut state)?;
+        state.is_opened = true;
+        state.allows_release = false;
+
+        Ok(WatchdogHandle {
+            device: self.clone(),
+        })
+    }
+
+    fn start(&self, state: &mut DeviceState) -> Result<(), WatchdogError> {
+        if state.is_running {
+            return self.watchdog.ping();
+        }
+        self.watchdog.start(state.timeout)?;
+        state.is_running = true;
+        Ok(())
+    }
+
+    fn stop(&self, state: &mut DeviceState) -> Result<(), WatchdogError> {
+        if !state.is_running {
+            return Ok(());
+        }
+        self.watchdog.stop()?;
+        state.is_running = false;
+        Ok(())
+    }
+
+    fn ping(&self, state: &DeviceState) -> Result<(), WatchdogError> {
+        if !state.is_running {
+            return Ok(());
+        }
+        self.watchdog.ping()
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock();
+        state.is_opened = false;
+
+        // Like Linux, the watchdog is always stopped if the magic close is not supported.
+        let should_stop = state.allows_release
+            || !self
+                .watchdog
+                .options()
+                .contains(WatchdogOptions::MAGICCLOSE);
+        if should_stop && self.stop(&mut state).is_ok() {
+            return;
+        }
+
+        if state.is_running {
+            error!("[Watchdog]: {} did not stop", self.name());
+            let _ = self.ping(&state);
+        }
+    }
+}
+
+/// An opened watchdog device.
+///
+/// When the handle is dropped, the watchdog is stopped if the magic character has been written
+/// (or the magic close is not supported). Otherwise, it keeps running.
+#[derive(Debug)]
+pub struct WatchdogHandle {
+    device: Arc<WatchdogDevice>,
+}
+
+impl WatchdogHandle {
+    pub fn device(&self) -> &Arc<WatchdogDevice> {
+        &self.device
+    }
+
+    /// Pings the watchdog after some bytes are written to the device.
+    ///
+    /// If the magic close is supported, the watchdog is allowed to be stopped when the handle is
+    /// closed if the bytes contain [`MAGIC_CLOSE_CHAR`]. This is reset by the next write.
+    ///
+    /// [`MAGIC_CLOSE_CHAR`]: crate::MAGIC_CLOSE_CHAR
+    pub fn write(&self, has_magic_close_char: bool) -> Result<(), WatchdogError> {
+        let mut state = self.device.state.lock();
+        state.allows_release =
+            has_magic_close_char && self.options().contains(WatchdogOptions::MAGICCLOSE);
+        self.device.ping(&state)
+    }
+
+    /// Pings the watchdog.
+    pub fn keepalive(&self) -> Result<(), WatchdogError> {
+        if !self.options().contains(WatchdogOptions::KEEPALIVEPING) {
+            return Err(WatchdogError::NotSupported);
+        }
+
+        let state = self.device.state.lock();
+        self.device.ping(&state)
+    }
+
+    /// Returns the options supported by the watchdog.
+    pub fn options(&self) -> WatchdogOptions {
+        self.device.watchdog.options()
+    }
+
+    /// Returns the current status.
+    pub fn status(&self) -> WatchdogOptions {
+        if self.device.state.lock().allows_release {
+            WatchdogOptions::MAGICCLOSE
+        } else {
+            WatchdogOptions::empty()
+        }
+    }
+
+    /// Returns the status at boot.
+    pub fn boot_status(&self) -> WatchdogOptions {
+        self.device.watchdog.boot_status()
+    }
+
+    /// Starts the watchdog.
+    pub fn enable(&self) -> Result<(), WatchdogError> {
+        let mut state = self.device.state.lock();
+        self.device.start(&mut state)
+    }
+
+    /// Stops the watchdog.
+    pub fn disable(&self) -> Result<(), WatchdogError> {
+        let mut state = self.device.state.lock();
+        self.device.stop(&mut state)
+    }
+
+    /// Returns the timeout in seconds.
+    pub fn timeout(&self) -> u32 {
+        self.device.state.lock().timeout
+    }
+
+    /// Sets the timeout in seconds and pings the watchdog.
+    pub fn set_timeout(&self, timeout: u32) -> Result<(), WatchdogError> {
+        if !self.options().contains(WatchdogOptions::SETTIMEOUT) {
+            return Err(WatchdogError::NotSupported);
+        }
+        if !self.device.watchdog.timeout_range().contains(&timeout) {
+            return Err(WatchdogError::InvalidArgs);
+        }
+
+        let mut state = self.device.state.lock();
+        self.device.watchdog.set_timeout(timeout)?;
+        state.timeout = timeout;
+        self.device.ping(&state)
+    }
+
+    /// Returns the number of seconds before the watchdog resets the system.
+    pub fn time_left(&self) -> Result<u32, WatchdogError> {
+        let _state = self.device.state.lock();
+        self.device
+            .watchdog
+            .time_left()
+            .ok_or(WatchdogError::NotSupported)
+    }
+}
+
+impl Drop for WatchdogHandle {
+    fn drop(&mut self) {
+        self.device.release();
+    }
+}
+
+static DEVICES: Mutex<Vec<Arc<WatchdogDevice>>> = Mutex::new(Vec::new());
+
+/// Registers a watchdog as a device.
+///
+/// The watchdog should be stopped when it is registered.
+pub fn register_watchdog(watchdog: Arc<dyn Watchdog>) -> Arc<WatchdogDevice> {
+    let mut devices = DEVICES.lock();
+
+    let device = Arc::new(WatchdogDevice {
+        index: devices.len() as u32,
+        state: Mutex::new(DeviceState {
+            is_opened: false,
+            is_running: false,
+            timeout: watchdog.default_timeout(),
+            allows_release: false,
+        }),
+        watchdog,
+    });
+    info!(
+        "[Watchdog]: Registered {} ({})",
+        device.name(),
+        device.watchdog.identity()
+    );
+
+    devices.push(device.clone());
+    device
+}
+
+/// Returns the device of the index.
+pub fn get_device(index: u32) -> Option<Arc<WatchdogDevice>> {
+    DEVICES.lock().get(index as usize).cloned()
+}
+
+/// Returns all the devices.
+pub fn all_devices() -> Vec<Arc<WatchdogDevice>> {
+    DEVICES.lock().clone()
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the watchdog timer in the Intel 6300ESB I/O controller hub, which is emulated by
+//! QEMU (`-device i6300esb`).
+//!
+//! The watchdog has two stages. The first timer starts after a ping, and the second timer starts
+//! after the first one expires. The system is reset when the second timer expires. Both timers
+//! are set to half of the timeout, and the interrupt of the first stage is disabled.
+//!
+//! Reference: Intel 6300ESB I/O Controller Hub Datasheet, Section 13.
+
+use alloc::sync::Arc;
+use core::ops::RangeInclusive;
+
+use log::warn;
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            cfg_space::{Bar, Command},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    io::IoMem,
+    mm::VmIoOnce,
+};
+
+use crate::{register_watchdog, Watchdog, WatchdogError, WatchdogOptions};
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(EsbDriver));
+}
+
+const INTEL_VENDOR_ID: u16 = 0x8086;
+const ESB_WDT_DEVICE_ID: u16 = 0x25ab;
+
+/// The index of the BAR that contains the watchdog registers.
+const ESB_BAR_INDEX: u8 = 0;
+/// The number of the watchdog registers in bytes.
+const ESB_REGS_SIZE: usize = 16;
+
+// The registers in the PCI configuration space.
+const ESB_CONFIG_REG: u16 = 0x60;
+const ESB_LOCK_REG: u16 = 0x68;
+
+// The memory-mapped registers.
+const ESB_TIMER1_REG: usize = 0x00;
+const ESB_TIMER2_REG: usize = 0x04;
+const ESB_RELOAD_REG: usize = 0x0c;
+
+// The bits of the lock register.
+const ESB_WDT_ENABLE: u8 = 1 << 1;
+const ESB_WDT_LOCK: u8 = 1 << 0;
+
+/// The config register value that enables the reset output, selects the 1 kHz clock, and
+/// disables the interrupt of the first stage.
+const ESB_CONFIG_WDT_MODE: u16 = 0x0003;
+
+// The bits of the reload register.
+const ESB_WDT_TIMEOUT: u16 = 1 << 9;
+const ESB_WDT_RELOAD: u16 = 1 << 8;
+
+// The sequence to unlock the registers, which is needed before each write.
+const ESB_UNLOCK1: u16 = 0x80;
+const ESB_UNLOCK2: u16 = 0x86;
+
+/// The default timeout in seconds, which is the same as Linux.
+const DEFAULT_TIMEOUT: u32 = 30;
+/// The range of the timeouts in seconds, which is limited by the 20-bit timers.
+const TIMEOUT_RANGE: RangeInclusive<u32> = 1..=2046;
+
+#[derive(Debug)]
+struct EsbDriver;
+
+impl PciDriver for EsbDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.vendor_id != INTEL_VENDOR_ID || device_id.device_id != ESB_WDT_DEVICE_ID {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(ESB_BAR_INDEX).clone() else {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        };
+        if (bar.size() as usize) < ESB_REGS_SIZE {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        }
+        device.set_command(device.command() | Command::MEMORY_SPACE);
+
+        let mut watchdog = EsbWatchdog {
+            device_id,
+            io_mem: bar.io_mem().clone(),
+            boot_status: WatchdogOptions::empty(),
+            device,
+        };
+        watchdog.init();
+
+        let watchdog = Arc::new(watchdog);
+        register_watchdog(watchdog.clone());
+
+        Ok(watchdog)
+    }
+}
+
+#[derive(Debug)]
+struct EsbWatchdog {
+    device_id: PciDeviceId,
+    device: PciCommonDevice,
+    io_mem: IoMem,
+    boot_status: WatchdogOptions,
+}
+
+impl EsbWatchdog {
+    /// Initializes the watchdog in the watchdog mode, and leaves it stopped.
+    fn init(&mut self) {
+        self.device
+            .write_device_config16(ESB_CONFIG_REG, ESB_CONFIG_WDT_MODE);
+
+        if self.device.read_device_config8(ESB_LOCK_REG) & ESB_WDT_LOCK != 0 {
+            warn!("[Watchdog]: The i6300esb watchdog is locked and cannot be stopped");
+        }
+        self.device.write_device_config8(ESB_LOCK_REG, 0);
+
+        // Check whether the last reboot was caused by the watchdog, and clear the flag.
+        self.unlock_registers();
+        let reload: u16 = self.io_mem.read_once(ESB_RELOAD_REG).unwrap();
+        if reload & ESB_WDT_TIMEOUT != 0 {
+            self.boot_status = WatchdogOptions::CARDRESET;
+        }
+        self.unlock_registers();
+        self.write_reload(ESB_WDT_TIMEOUT | ESB_WDT_RELOAD);
+
+        self.write_timers(DEFAULT_TIMEOUT);
+    }
+
+    fn unlock_registers(&self) {
+        self.write_reload(ESB_UNLOCK1);
+        self.write_reload(ESB_UNLOCK2);
+    }
+
+    fn write_reload(&self, value: u16) {
+        self.io_mem.write_once(ESB_RELOAD_REG, &value).unwrap();
+    }
+
+    /// Sets both timers to half of the timeout and reloads them.
+    fn write_timers(&self, timeout: u32) {
+        // The timers count at 1 kHz, so each one counts `timeout * 512` ticks, which is about
+        // half of the timeout.
+        let value = timeout << 9;
+
+        self.unlock_registers();
+        self.io_mem.write_once(ESB_TIMER1_REG, &value).unwrap();
+        self.unlock_registers();
+        self.io_mem.write_once(ESB_TIMER2_REG, &value).unwrap();
+
+        self.unlock_registers();
+        self.write_reload(ESB_WDT_RELOAD);
+    }
+}
+
+impl PciDevice for EsbWatchdog {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
+
+impl Watchdog for EsbWatchdog {
+    fn identity(&self) -> &str {
+        "Intel 6300ESB"
+    }
+
+    fn options(&self) -> WatchdogOptions {
+        WatchdogOptions::SETTIMEOUT | WatchdogOptions::KEEPALIVEPING | WatchdogOptions::MAGICCLOSE
+    }
+
+    fn timeout_range(&self) -> RangeInclusive<u32> {
+        TIMEOUT_RANGE
+    }
+
+    fn default_timeout(&self) -> u32 {
+        DEFAULT_TIMEOUT
+    }
+
+    fn start(&self, timeout: u32) -> Result<(), WatchdogError> {
+        self.write_timers(timeout);
+        self.device
+            .write_device_config8(ESB_LOCK_REG, ESB_WDT_ENABLE);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), WatchdogError> {
+        self.unlock_registers();
+        self.device.write_device_config8(ESB_LOCK_REG, 0);
+
+        // The watchdog cannot be stopped if it has been locked.
+        if self.device.read_device_config8(ESB_LOCK_REG) & ESB_WDT_ENABLE != 0 {
+            return Err(WatchdogError::Io);
+        }
+        Ok(())
+    }
+
+    fn ping(&self) -> Result<(), WatchdogError> {
+        self.unlock_registers();
+        self.write_reload(ESB_WDT_RELOAD);
+        Ok(())
+    }
+
+    fn set_timeout(&self, timeout: u32) -> Result<(), WatchdogError> {
+        self.write_timers(timeout);
+        Ok(())
+    }
+
+    fn boot_status(&self) -> WatchdogOptions {
+        self.boot_status
+    }
+}
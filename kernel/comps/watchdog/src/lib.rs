@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The watchdog subsystem of Asterinas.
+//!
+//! The subsystem follows the model of Linux:
+//!  - A watchdog ([`Watchdog`]) resets the system if it is not pinged within its timeout. Each
+//!    watchdog is registered as a device ([`WatchdogDevice`]), which is identified by its index.
+//!  - A device is used exclusively through a handle ([`WatchdogHandle`]). The watchdog is started
+//!    when the handle is opened, and it is stopped when the handle is closed only if the magic
+//!    character has been written before (i.e., "magic close"). Otherwise, the watchdog keeps
+//!    running, so that a supervisor that crashes leads to a reset.
+//!
+//! Two watchdogs are provided: the Intel 6300ESB watchdog, which is emulated by QEMU, and a
+//! software watchdog, which panics if it is not pinged in time.
+//!
+//! Userspace can access the devices via `/dev/watchdogN` and `/dev/watchdog`, which are
+//! implemented by the kernel on top of [`get_device`].
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod device;
+mod i6300esb;
+mod softdog;
+
+use core::{fmt::Debug, ops::RangeInclusive};
+
+use bitflags::bitflags;
+use component::{init_component, ComponentInitError};
+pub use device::{all_devices, get_device, register_watchdog, WatchdogDevice, WatchdogHandle};
+
+/// The maximum length of the identity of a watchdog, including the terminating NUL.
+pub const IDENTITY_MAX: usize = 32;
+
+/// The character that allows the watchdog to be stopped when its handle is closed.
+pub const MAGIC_CLOSE_CHAR: u8 = b'V';
+
+/// A watchdog, which resets the system if it is not pinged within its timeout.
+///
+/// The methods are called with the device locked, so they are never called concurrently.
+pub trait Watchdog: Send + Sync + Debug {
+    /// Returns the identity of the watchdog.
+    fn identity(&self) -> &str;
+
+    /// Returns the options supported by the watchdog.
+    fn options(&self) -> WatchdogOptions;
+
+    /// Returns the range of the timeouts in seconds.
+    fn timeout_range(&self) -> RangeInclusive<u32>;
+
+    /// Returns the initial timeout in seconds.
+    fn default_timeout(&self) -> u32;
+
+    /// Starts the watchdog with the timeout.
+    fn start(&self, timeout: u32) -> Result<(), WatchdogError>;
+
+    /// Stops the watchdog.
+    fn stop(&self) -> Result<(), WatchdogError>;
+
+    /// Pings the running watchdog, so that the timeout starts over.
+    fn ping(&self) -> Result<(), WatchdogError>;
+
+    /// Sets the timeout in seconds, which is in the range of the timeouts.
+    ///
+    /// If the watchdog is running, the new timeout should take effect immediately.
+    fn set_timeout(&self, timeout: u32) -> Result<(), WatchdogError>;
+
+    /// Returns the number of seconds before the running watchdog resets the system.
+    ///
+    /// Returns `None` if it is not supported.
+    fn time_left(&self) -> Option<u32> {
+        None
+    }
+
+    /// Returns the status of the watchdog at boot, e.g., whether the last reboot was caused by
+    /// the watchdog.
+    fn boot_status(&self) -> WatchdogOptions {
+        WatchdogOptions::empty()
+    }
+}
+
+bitflags! {
+    /// The options supported by a watchdog, which are also used as the status bits.
+    ///
+    /// The values are the same as `WDIOF_*` of Linux.
+    pub struct WatchdogOptions: u32 {
+        /// The system was reset due to CPU overheat.
+        const OVERHEAT      = 0x0001;
+        /// The fan failed.
+        const FANFAULT      = 0x0002;
+        /// The external relay 1.
+        const EXTERN1       = 0x0004;
+        /// The external relay 2.
+        const EXTERN2       = 0x0008;
+        /// The power was bad.
+        const POWERUNDER    = 0x0010;
+        /// The last reboot was caused by the watchdog.
+        const CARDRESET     = 0x0020;
+        /// The power was over voltage.
+        const POWEROVER     = 0x0040;
+        /// The timeout can be set.
+        const SETTIMEOUT    = 0x0080;
+        /// The magic close is supported.
+        const MAGICCLOSE    = 0x0100;
+        /// The pretimeout can be set.
+        const PRETIMEOUT    = 0x0200;
+        /// The watchdog triggers an alarm instead of a reboot.
+        const ALARMONLY     = 0x0400;
+        /// The watchdog can be pinged with `WDIOC_KEEPALIVE`.
+        const KEEPALIVEPING = 0x8000;
+    }
+}
+
+/// The errors of watchdog operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogError {
+    /// The operation is not supported by the watchdog.
+    NotSupported,
+    /// The arguments are invalid.
+    InvalidArgs,
+    /// The watchdog is opened by another user.
+    Busy,
+    /// Other errors reported by the watchdog.
+    Io,
+}
+
+#[init_component]
+fn watchdog_init() -> Result<(), ComponentInitError> {
+    // The hardware watchdogs are registered first, so that one of them becomes the default
+    // watchdog if there is any.
+    i6300esb::init();
+    softdog::init();
+    Ok(())
+}
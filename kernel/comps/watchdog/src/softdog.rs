@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The software watchdog.
+//!
+//! The watchdog checks its deadline in the timer interrupts, and panics if it is not pinged
+//! before the deadline. Unlike a hardware watchdog, it cannot recover from the hangs that stop
+//! the timer interrupts, e.g., a CPU spinning with the interrupts disabled.
+
+use alloc::sync::Arc;
+use core::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use ostd::{arch::timer::TIMER_FREQ, timer::Jiffies};
+
+use crate::{register_watchdog, Watchdog, WatchdogError, WatchdogOptions};
+
+/// The default timeout in seconds, which is the same as Linux's `TIMER_MARGIN`.
+const DEFAULT_TIMEOUT: u32 = 60;
+/// The maximum timeout in seconds, which is the same as Linux.
+const MAX_TIMEOUT: u32 = 65535;
+
+/// The deadline in jiffies, or zero if the watchdog is stopped.
+static DEADLINE: AtomicU64 = AtomicU64::new(0);
+/// The timeout in seconds.
+static TIMEOUT: AtomicU32 = AtomicU32::new(DEFAULT_TIMEOUT);
+
+pub(super) fn init() {
+    ostd::timer::register_callback(check_deadline);
+    register_watchdog(Arc::new(SoftWatchdog));
+}
+
+fn check_deadline() {
+    let deadline = DEADLINE.load(Ordering::Relaxed);
+    if deadline != 0 && Jiffies::elapsed().as_u64() >= deadline {
+        panic!("[Watchdog]: The software watchdog is not pinged in time");
+    }
+}
+
+fn update_deadline() {
+    let timeout_jiffies = TIMEOUT.load(Ordering::Relaxed) as u64 * TIMER_FREQ;
+    DEADLINE.store(
+        Jiffies::elapsed().as_u64() + timeout_jiffies,
+        Ordering::Relaxed,
+    );
+}
+
+#[derive(Debug)]
+struct SoftWatchdog;
+
+impl Watchdog for SoftWatchdog {
+    fn identity(&self) -> &str {
+        "Software Watchdog"
+    }
+
+    fn options(&self) -> WatchdogOptions {
+        WatchdogOptions::SETTIMEOUT | WatchdogOptions::KEEPALIVEPING | WatchdogOptions::MAGICCLOSE
+    }
+
+    fn timeout_range(&self) -> RangeInclusive<u32> {
+        1..=MAX_TIMEOUT
+    }
+
+    fn default_timeout(&self) -> u32 {
+        DEFAULT_TIMEOUT
+    }
+
+    fn start(&self, timeout: u32) -> Result<(), WatchdogError> {
+        TIMEOUT.store(timeout, Ordering::Relaxed);
+        update_deadline();
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), WatchdogError> {
+        DEADLINE.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn ping(&self) -> Result<(), WatchdogError> {
+        update_deadline();
+        Ok(())
+    }
+
+    fn set_timeout(&self, timeout: u32) -> Result<(), WatchdogError> {
+        TIMEOUT.store(timeout, Ordering::Relaxed);
+        if DEADLINE.load(Ordering::Relaxed) != 0 {
+            update_deadline();
+        }
+        Ok(())
+    }
+
+    fn time_left(&self) -> Option<u32> {
+        let deadline = DEADLINE.load(Ordering::Relaxed);
+        if deadline == 0 {
+            return Some(0);
+        }
+        let left_jiffies = deadline.saturating_sub(Jiffies::elapsed().as_u64());
+        Some((left_jiffies / TIMER_FREQ) as u32)
+    }
+}
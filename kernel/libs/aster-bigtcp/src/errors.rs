@@ -112,3 +112,27 @@ pub mod udp {
         }
     }
 }
+
+pub mod icmp {
+    /// An error returned by [`IcmpSocket::send`].
+    ///
+    /// [`IcmpSocket::send`]: crate::socket::IcmpSocket::send
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum SendError {
+        Unaddressable,
+        BufferFull,
+        /// The message is too large.
+        TooLarge,
+        /// The message is not a valid ICMP echo request.
+        InvalidMessage,
+    }
+
+    /// An error returned by [`IcmpSocket::recv`].
+    ///
+    /// [`IcmpSocket::recv`]: crate::socket::IcmpSocket::recv
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum RecvError {
+        /// No messages have been received.
+        Exhausted,
+    }
+}
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{iface::ScheduleNextPoll, socket::SocketEventObserver};
+use crate::{
+    iface::{FrameTap, PacketFilter, ScheduleNextPoll},
+    socket::SocketEventObserver,
+    time::PacketClock,
+};
 
 /// Extension to be implemented by users of this crate.
 ///
@@ -14,9 +18,21 @@ pub trait Ext {
     /// The type for ifaces to schedule the next poll.
     type ScheduleNextPoll: ScheduleNextPoll;
 
+    /// The type for ifaces to pass the sent and received frames to the taps.
+    type FrameTap: FrameTap;
+
+    /// The type for ifaces to filter the IP packets.
+    type PacketFilter: PacketFilter;
+
     /// The type for TCP sockets to observe events.
     type TcpEventObserver: SocketEventObserver + Clone;
 
     /// The type for UDP sockets to observe events.
     type UdpEventObserver: SocketEventObserver;
+
+    /// The type for ICMP sockets to observe events.
+    type IcmpEventObserver: SocketEventObserver;
+
+    /// The type for UDP sockets to timestamp the packets.
+    type PacketClock: PacketClock;
 }
@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{
-    collections::btree_map::{BTreeMap, Entry},
+    collections::{
+        btree_map::{BTreeMap, Entry},
+        vec_deque::VecDeque,
+    },
     string::String,
     sync::Arc,
+    vec,
     vec::Vec,
 };
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -13,22 +17,26 @@ use bitflags::bitflags;
 use int_to_c_enum::TryFromInt;
 use ostd::sync::{SpinLock, SpinLockGuard};
 use smoltcp::{
-    iface::{packet::Packet, Context},
-    phy::Device,
-    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv4Packet},
+    iface::{packet::Packet, Context, Route},
+    phy::{Device, DeviceCapabilities, TxToken},
+    wire::{IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
 };
 
 use super::{
-    poll::{FnHelper, PollContext, SocketTableAction},
+    filter::{self, OutgoingPacket, EGRESS_HOOKS},
+    frag::{self, Reassembler},
+    ipv6,
+    poll::{FnHelper, IpPacket, PollContext, SocketTableAction},
     poll_iface::PollableIface,
     port::BindPortConfig,
+    rx_handler::RxHandler,
     time::get_network_timestamp,
     Iface,
 };
 use crate::{
     errors::BindError,
     ext::Ext,
-    socket::{TcpListenerBg, UdpSocketBg},
+    socket::{IcmpSocketBg, TcpListenerBg, UdpSocketBg},
     socket_table::SocketTable,
 };
 
@@ -41,6 +49,10 @@ pub struct IfaceCommon<E: Ext> {
     interface: SpinLock<PollableIface<E>, BottomHalfDisabled>,
     used_ports: SpinLock<BTreeMap<u16, usize>, BottomHalfDisabled>,
     sockets: SpinLock<SocketTable<E>, BottomHalfDisabled>,
+    rx_handler: SpinLock<Option<Arc<dyn RxHandler>>, BottomHalfDisabled>,
+    reassembler: SpinLock<Reassembler, BottomHalfDisabled>,
+    /// The fragments that are waiting to be sent to the device, including the link-layer headers.
+    pending_frames: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
     sched_poll: E::ScheduleNextPoll,
 }
 
@@ -62,6 +74,9 @@ impl<E: Ext> IfaceCommon<E> {
             interface: SpinLock::new(PollableIface::new(interface)),
             used_ports: SpinLock::new(BTreeMap::new()),
             sockets: SpinLock::new(SocketTable::new()),
+            rx_handler: SpinLock::new(None),
+            reassembler: SpinLock::new(Reassembler::new()),
+            pending_frames: SpinLock::new(VecDeque::new()),
             sched_poll,
         }
     }
@@ -90,9 +105,66 @@ impl<E: Ext> IfaceCommon<E> {
         self.interface.lock().prefix_len()
     }
 
+    pub(super) fn ipv4_addrs(&self) -> Vec<Ipv4Cidr> {
+        self.interface.lock().ipv4_addrs()
+    }
+
+    pub(super) fn ipv6_addrs(&self) -> Vec<Ipv6Cidr> {
+        self.interface.lock().ipv6_addrs().to_vec()
+    }
+
+    pub(super) fn ipv6_src_addr(&self, dst_addr: &Ipv6Address) -> Option<Ipv6Address> {
+        ipv6::select_src_addr(self.interface.lock().ipv6_addrs(), dst_addr)
+    }
+
+    pub(super) fn add_ipv6_addr(&self, ipv6_cidr: Ipv6Cidr) -> bool {
+        self.interface.lock().add_ipv6_addr(ipv6_cidr)
+    }
+
+    pub(super) fn add_ipv4_addr(&self, ipv4_cidr: Ipv4Cidr) -> bool {
+        self.interface.lock().add_ipv4_addr(ipv4_cidr)
+    }
+
+    pub(super) fn remove_ip_addr(&self, ip_cidr: IpCidr) -> bool {
+        self.interface.lock().remove_ip_addr(ip_cidr)
+    }
+
+    pub(super) fn routes(&self) -> Vec<Route> {
+        self.interface.lock().routes()
+    }
+
+    pub(super) fn add_route(&self, route: Route) -> bool {
+        self.interface.lock().add_route(route)
+    }
+
+    pub(super) fn remove_route(&self, cidr: &IpCidr) -> bool {
+        self.interface.lock().remove_route(cidr)
+    }
+
+    pub(super) fn set_ipv6_gateway(&self, gateway: Ipv6Address) {
+        self.interface.lock().set_ipv6_gateway(gateway);
+    }
+
     pub(super) fn sched_poll(&self) -> &E::ScheduleNextPoll {
         &self.sched_poll
     }
+
+    pub(super) fn rx_handler(&self) -> Option<Arc<dyn RxHandler>> {
+        self.rx_handler.lock().clone()
+    }
+
+    pub(super) fn register_rx_handler(&self, handler: Arc<dyn RxHandler>) -> bool {
+        let mut rx_handler = self.rx_handler.lock();
+        if rx_handler.is_some() {
+            return false;
+        }
+        *rx_handler = Some(handler);
+        true
+    }
+
+    pub(super) fn unregister_rx_handler(&self) -> Option<Arc<dyn RxHandler>> {
+        self.rx_handler.lock().take()
+    }
 }
 
 /// An allocator that allocates a unique index for each interface.
@@ -100,7 +172,7 @@ impl<E: Ext> IfaceCommon<E> {
 // FIXME: This allocator is specific to each network namespace.
 pub static INTERFACE_INDEX_ALLOCATOR: AtomicU32 = AtomicU32::new(1);
 
-// Lock order: `interface` -> `sockets`
+// Lock order: `interface` -> `sockets` -> `reassembler` -> `pending_frames`
 impl<E: Ext> IfaceCommon<E> {
     /// Acquires the lock to the interface.
     pub(crate) fn interface(&self) -> SpinLockGuard<'_, PollableIface<E>, BottomHalfDisabled> {
@@ -120,10 +192,11 @@ impl<E: Ext> IfaceCommon<E> {
     pub(super) fn bind(
         &self,
         iface: Arc<dyn Iface<E>>,
+        addr: IpAddress,
         config: BindPortConfig,
     ) -> core::result::Result<BoundPort<E>, BindError> {
         let port = self.bind_port(config)?;
-        Ok(BoundPort { iface, port })
+        Ok(BoundPort { iface, addr, port })
     }
 
     /// Allocates an unused ephemeral port.
@@ -185,9 +258,14 @@ impl<E: Ext> IfaceCommon<E> {
         sockets.insert_udp_socket(socket);
     }
 
+    pub(crate) fn register_icmp_socket(&self, socket: Arc<IcmpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        sockets.insert_icmp_socket(socket);
+    }
+
     pub(crate) fn remove_tcp_listener(&self, socket: &Arc<TcpListenerBg<E>>) {
         let mut sockets = self.sockets.lock();
-        let removed = sockets.remove_listener(socket.listener_key());
+        let removed = sockets.remove_listener(socket);
         debug_assert!(removed.is_some());
     }
 
@@ -196,6 +274,12 @@ impl<E: Ext> IfaceCommon<E> {
         let removed = sockets.remove_udp_socket(socket);
         debug_assert!(removed.is_some());
     }
+
+    pub(crate) fn remove_icmp_socket(&self, socket: &Arc<IcmpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        let removed = sockets.remove_icmp_socket(socket);
+        debug_assert!(removed.is_some());
+    }
 }
 
 impl<E: Ext> IfaceCommon<E> {
@@ -211,19 +295,30 @@ impl<E: Ext> IfaceCommon<E> {
             &'pkt [u8],
             &'cx mut Context,
             D::TxToken<'tx>,
-            Option<(Ipv4Packet<&'pkt [u8]>, D::TxToken<'tx>)>,
+            Option<(IpPacket<&'pkt [u8]>, D::TxToken<'tx>)>,
         >,
         Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
     {
+        let now = get_network_timestamp();
         let mut interface = self.interface();
-        interface.context_mut().now = get_network_timestamp();
+        interface.context_mut().now = now;
 
         let mut sockets = self.sockets.lock();
         let mut socket_actions = Vec::new();
 
-        let mut context = PollContext::new(interface.as_mut(), &sockets, &mut socket_actions);
-        context.poll_ingress(device, &mut process_phy, &mut dispatch_phy);
-        context.poll_egress(device, &mut dispatch_phy);
+        let mut context = PollContext::new(
+            interface.as_mut(),
+            self.index,
+            &sockets,
+            &mut socket_actions,
+        );
+        context.poll_ingress(
+            device,
+            &mut self.reassembler.lock(),
+            &mut process_phy,
+            &mut dispatch_phy,
+        );
+        context.poll_egress(device, &self.pending_frames, &mut dispatch_phy);
 
         // Insert new connections and remove dead connections.
         for action in socket_actions.into_iter() {
@@ -238,10 +333,74 @@ impl<E: Ext> IfaceCommon<E> {
             }
         }
 
+        // If the device runs out of buffers before all fragments are sent, the remaining
+        // fragments should be sent as soon as possible.
+        if !self.pending_frames.lock().is_empty() {
+            return Some(now.total_millis() as u64);
+        }
+
         // Note that only TCP connections can have timers set, so as far as the time to poll is
         // concerned, we only need to consider TCP connections.
         interface.next_poll_at_ms()
     }
+
+    /// Filters a packet that is about to be sent to the device.
+    ///
+    /// This method returns the packet that should be sent, or `None` if the packet is discarded.
+    pub(super) fn filter_egress<'a, 'p>(
+        &self,
+        pkt: &'a Packet<'p>,
+        iface_cx: &Context,
+    ) -> Option<OutgoingPacket<'a, 'p>> {
+        // TODO: Notify the local sockets if their packets are rejected. Currently, rejected
+        // packets are silently discarded, just like dropped packets.
+        filter::filter_packet::<E::PacketFilter>(self.index, EGRESS_HOOKS, pkt, iface_cx).ok()
+    }
+
+    /// Consumes the token and emits an IP packet after the link-layer header.
+    ///
+    /// If an IPv4 packet does not fit in the MTU of the device, it is fragmented. The first
+    /// fragment is sent with the token, and the remaining fragments are sent with the tokens
+    /// obtained later (see [`PollContext::poll_egress`]).
+    pub(super) fn transmit_ip<T: TxToken>(
+        &self,
+        pkt: &OutgoingPacket,
+        caps: &DeviceCapabilities,
+        link_header_len: usize,
+        emit_link_header: impl Fn(&mut [u8]),
+        tx_token: T,
+    ) {
+        let ip_len = pkt.buffer_len();
+        let ip_mtu = caps.max_transmission_unit.saturating_sub(link_header_len);
+
+        // TODO: Support IPv6 fragmentation. Currently, IPv6 packets that exceed the MTU are sent
+        // as is, and the device may drop them.
+        if ip_len <= ip_mtu || !matches!(pkt.dst_addr(), IpAddress::Ipv4(_)) {
+            tx_token.consume(link_header_len + ip_len, |buffer| {
+                emit_link_header(buffer);
+                pkt.emit(&mut buffer[link_header_len..], caps);
+            });
+            return;
+        }
+
+        let mut ip_bytes = vec![0; ip_len];
+        pkt.emit(&mut ip_bytes, caps);
+
+        let mut frames = frag::fragment_ipv4(&ip_bytes, ip_mtu, link_header_len).into_iter();
+        let Some(mut first_frame) = frames.next() else {
+            return;
+        };
+
+        emit_link_header(&mut first_frame);
+        tx_token.consume(first_frame.len(), |buffer| {
+            buffer.copy_from_slice(&first_frame)
+        });
+
+        self.pending_frames.lock().extend(frames.map(|mut frame| {
+            emit_link_header(&mut frame);
+            frame
+        }));
+    }
 }
 
 /// A port bound to an iface.
@@ -251,6 +410,7 @@ impl<E: Ext> IfaceCommon<E> {
 // FIXME: TCP and UDP ports are independent. Find a way to track the protocol here.
 pub struct BoundPort<E: Ext> {
     iface: Arc<dyn Iface<E>>,
+    addr: IpAddress,
     port: u16,
 }
 
@@ -266,12 +426,8 @@ impl<E: Ext> BoundPort<E> {
     }
 
     /// Returns the bound endpoint.
-    pub fn endpoint(&self) -> Option<IpEndpoint> {
-        let ip_addr = {
-            let ipv4_addr = self.iface().ipv4_addr()?;
-            IpAddress::Ipv4(ipv4_addr)
-        };
-        Some(IpEndpoint::new(ip_addr, self.port))
+    pub fn endpoint(&self) -> IpEndpoint {
+        IpEndpoint::new(self.addr, self.port)
     }
 }
 
@@ -308,6 +464,9 @@ pub enum InterfaceType {
     LOOPBACK = 772,
     /// Localtalk device
     LOCALTALK = 773,
+
+    /// Zero header length (e.g., TUN devices)
+    NONE = 0xFFFE,
     // TODO: This enum is not exhaustive
 }
 
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{vec, vec::Vec};
+
+use smoltcp::{
+    iface::{packet::Packet, Context},
+    phy::DeviceCapabilities,
+    wire::IpAddress,
+};
+
+use super::poll::IpPacket;
+
+/// A trait for ifaces to filter the IP packets at the netfilter-style hooks.
+///
+/// Unlike [`FrameTap`], the packets are filtered at the IP layer, so the packets that are
+/// delivered between local addresses without going through the devices are also filtered.
+///
+/// [`FrameTap`]: super::FrameTap
+pub trait PacketFilter {
+    /// Returns whether the packets need to be filtered.
+    ///
+    /// If this method returns `false`, all packets are accepted without being passed to
+    /// [`Self::filter`]. This avoids the cost of emitting the packets generated by the stack when
+    /// there is nothing to filter.
+    fn is_active() -> bool;
+
+    /// Filters an IP packet that traverses the hooks in order.
+    ///
+    /// The packet starts with the IP header and belongs to the iface with the given index.
+    ///
+    /// The filter may rewrite the addresses and the ports of the packet (e.g., to perform NAT),
+    /// in which case it is responsible for updating the checksums. The length of the packet and
+    /// the layout of its headers must not be changed.
+    fn filter(iface_index: u32, hooks: &[FilterHook], packet: &mut [u8]) -> FilterVerdict;
+}
+
+/// A netfilter-style hook.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter.h>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterHook {
+    /// The hook for received packets before routing decisions are made.
+    PreRouting,
+    /// The hook for received packets that are destined for local addresses.
+    Input,
+    /// The hook for received packets that are forwarded to other hosts.
+    ///
+    /// Packets are never forwarded, so no packets traverse this hook currently.
+    Forward,
+    /// The hook for packets that are sent from local addresses.
+    Output,
+    /// The hook for packets that are about to leave the host.
+    PostRouting,
+}
+
+/// The verdict of a filtered packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterVerdict {
+    /// The packet goes on.
+    Accept,
+    /// The packet is silently discarded.
+    Drop,
+    /// The packet is discarded, and the sender is notified with an error packet.
+    Reject(RejectWith),
+}
+
+/// The error packet that is sent to notify the sender of a rejected packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectWith {
+    /// A TCP RST segment.
+    ///
+    /// If the rejected packet is not a TCP segment, an ICMP Port Unreachable message is sent
+    /// instead.
+    TcpReset,
+    /// An ICMP (or ICMPv6) Destination Unreachable message.
+    IcmpUnreachable(IcmpUnreachable),
+}
+
+/// The reason in an ICMP (or ICMPv6) Destination Unreachable message.
+///
+/// The reasons are available in both ICMP and ICMPv6, so they are independent of the IP version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpUnreachable {
+    /// There is no route to the destination.
+    NoRoute,
+    /// The destination host is unreachable.
+    Host,
+    /// The destination port is unreachable.
+    Port,
+    /// The communication is administratively prohibited.
+    AdminProhibited,
+}
+
+/// The hooks traversed by the packets received from the devices.
+pub(super) const INGRESS_HOOKS: &[FilterHook] = &[FilterHook::PreRouting, FilterHook::Input];
+
+/// The hooks traversed by the packets sent to the devices.
+pub(super) const EGRESS_HOOKS: &[FilterHook] = &[FilterHook::Output, FilterHook::PostRouting];
+
+/// The hooks traversed by the packets delivered between local addresses.
+///
+/// Such packets do not go through the devices, but they should be filtered as if they are sent
+/// and then received by the loopback device.
+pub(super) const LOCAL_HOOKS: &[FilterHook] = &[
+    FilterHook::Output,
+    FilterHook::PostRouting,
+    FilterHook::PreRouting,
+    FilterHook::Input,
+];
+
+/// A packet generated by the stack that is going to be sent on.
+pub(super) enum OutgoingPacket<'a, 'p> {
+    /// The packet that is not filtered because the filter is inactive.
+    Repr(&'a Packet<'p>),
+    /// The packet that has been emitted and filtered.
+    ///
+    /// The filter may have rewritten the packet, so it must be sent as is.
+    Filtered(IpPacket<Vec<u8>>),
+}
+
+impl OutgoingPacket<'_, '_> {
+    /// Returns the destination address.
+    pub(super) fn dst_addr(&self) -> IpAddress {
+        match self {
+            Self::Repr(pkt) => pkt.ip_repr().dst_addr(),
+            Self::Filtered(IpPacket::Ipv4(pkt)) => IpAddress::Ipv4(pkt.dst_addr()),
+            Self::Filtered(IpPacket::Ipv6(pkt)) => IpAddress::Ipv6(pkt.dst_addr()),
+        }
+    }
+
+    /// Returns the length of the packet, including the IP header.
+    pub(super) fn buffer_len(&self) -> usize {
+        match self {
+            Self::Repr(pkt) => pkt.ip_repr().buffer_len(),
+            Self::Filtered(pkt) => pkt.as_bytes().len(),
+        }
+    }
+
+    /// Emits the packet, including the IP header, to the buffer.
+    pub(super) fn emit(&self, buffer: &mut [u8], caps: &DeviceCapabilities) {
+        match self {
+            Self::Repr(pkt) => {
+                let ip_repr = pkt.ip_repr();
+                ip_repr.emit(&mut buffer[..], &caps.checksum);
+                pkt.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], caps);
+            }
+            Self::Filtered(pkt) => {
+                let bytes = pkt.as_bytes();
+                buffer[..bytes.len()].copy_from_slice(bytes);
+            }
+        }
+    }
+}
+
+/// Filters a packet that is generated by the stack.
+///
+/// The packet is emitted only if the filter is active. If the packet is accepted, this method
+/// returns the packet that should be sent on.
+pub(super) fn filter_packet<'a, 'p, F: PacketFilter>(
+    iface_index: u32,
+    hooks: &[FilterHook],
+    pkt: &'a Packet<'p>,
+    iface_cx: &Context,
+) -> Result<OutgoingPacket<'a, 'p>, FilterVerdict> {
+    if !F::is_active() {
+        return Ok(OutgoingPacket::Repr(pkt));
+    }
+
+    let ip_repr = pkt.ip_repr();
+    let mut buffer = vec![0u8; ip_repr.buffer_len()];
+    ip_repr.emit(&mut buffer[..], &iface_cx.checksum_caps());
+    pkt.emit_payload(
+        &ip_repr,
+        &mut buffer[ip_repr.header_len()..],
+        &iface_cx.caps,
+    );
+
+    match F::filter(iface_index, hooks, &mut buffer) {
+        // The filter keeps the packet well-formed, so parsing the packet should not fail.
+        FilterVerdict::Accept => IpPacket::new_checked(buffer)
+            .map(OutgoingPacket::Filtered)
+            .ok_or(FilterVerdict::Drop),
+        verdict => Err(verdict),
+    }
+}
@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Fragmentation and reassembly of IPv4 packets.
+//!
+//! Outgoing packets that do not fit in the MTU of the device are split into fragments. Incoming
+//! fragments are queued until all fragments of the original packet have been received, after
+//! which the reassembled packet is processed as if it had been received as a whole.
+//!
+//! IPv6 packets are neither fragmented nor reassembled.
+
+use alloc::{collections::btree_map::BTreeMap, vec, vec::Vec};
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use smoltcp::{
+    time::{Duration, Instant},
+    wire::{Ipv4Address, Ipv4Packet},
+};
+
+/// The time after which incomplete packets are discarded.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/ip.h#L158>.
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The maximum number of packets that are being reassembled at the same time.
+const MAX_QUEUES: usize = 256;
+
+/// The maximum number of bytes that are buffered for reassembly.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/inet_fragment.c#L322>.
+const MAX_BUFFERED_LEN: usize = 4 * 1024 * 1024;
+
+/// The maximum length of an IPv4 packet.
+const MAX_PACKET_LEN: usize = u16::MAX as usize;
+
+/// Fragment offsets are measured in units of eight bytes.
+const FRAG_OFFSET_UNIT: usize = 8;
+
+/// Returns whether the IPv4 packet is a fragment of a larger packet.
+pub(super) fn is_fragment<T: AsRef<[u8]>>(pkt: &Ipv4Packet<T>) -> bool {
+    pkt.more_frags() || pkt.frag_offset() != 0
+}
+
+/// Splits an IPv4 packet into fragments that fit in the MTU.
+///
+/// Each fragment is preceded by `headroom` bytes, which are reserved for the link-layer header.
+/// If the packet cannot be fragmented, this method returns no fragments.
+//
+// Locally generated packets always have the "Don't Fragment" flag set by smoltcp, so the flag is
+// ignored here. This matches the default behavior of Linux (i.e., `IP_PMTUDISC_WANT`), which
+// fragments packets that exceed the MTU of the output device.
+pub(super) fn fragment_ipv4(packet: &[u8], ip_mtu: usize, headroom: usize) -> Vec<Vec<u8>> {
+    let Ok(pkt) = Ipv4Packet::new_checked(packet) else {
+        return Vec::new();
+    };
+
+    let header_len = pkt.header_len() as usize;
+    let max_payload_len = ip_mtu.saturating_sub(header_len) / FRAG_OFFSET_UNIT * FRAG_OFFSET_UNIT;
+    if max_payload_len == 0 {
+        return Vec::new();
+    }
+
+    let ident = NEXT_IDENT.fetch_add(1, Ordering::Relaxed);
+    let payload = pkt.payload();
+
+    // TODO: Only the options whose "copied" flags are set should be copied to the fragments
+    // other than the first one. Currently, smoltcp never emits any options.
+    payload
+        .chunks(max_payload_len)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let offset = i * max_payload_len;
+
+            let mut frame = vec![0; headroom + header_len + chunk.len()];
+            let fragment = &mut frame[headroom..];
+            fragment[..header_len].copy_from_slice(&packet[..header_len]);
+            fragment[header_len..].copy_from_slice(chunk);
+
+            let mut frag_pkt = Ipv4Packet::new_unchecked(fragment);
+            frag_pkt.set_total_len((header_len + chunk.len()) as u16);
+            frag_pkt.set_ident(ident);
+            frag_pkt.set_dont_frag(false);
+            frag_pkt.set_more_frags(offset + chunk.len() < payload.len());
+            frag_pkt.set_frag_offset(offset as u16);
+            frag_pkt.fill_checksum();
+
+            frame
+        })
+        .collect()
+}
+
+/// The identification of the next fragmented packet.
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(1);
+
+/// The queues of the incoming fragments that are waiting to be reassembled.
+pub(super) struct Reassembler {
+    queues: BTreeMap<FragKey, FragQueue>,
+    /// The total number of bytes buffered in all queues.
+    buffered_len: usize,
+}
+
+/// The fields that identify the fragments of the same packet.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc791#section-3.2>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FragKey {
+    src_addr: Ipv4Address,
+    dst_addr: Ipv4Address,
+    ident: u16,
+    protocol: u8,
+}
+
+struct FragQueue {
+    /// The IPv4 header of the first fragment, or `None` if it has not been received.
+    header: Option<Vec<u8>>,
+    /// The payload of the packet, where the bytes that have not been received are zeros.
+    payload: Vec<u8>,
+    /// The ranges of the payload that have been received.
+    ///
+    /// The ranges are sorted, and adjacent ranges are merged.
+    received: Vec<(usize, usize)>,
+    /// The length of the payload, or `None` if the last fragment has not been received.
+    total_len: Option<usize>,
+    expires_at: Instant,
+}
+
+/// The result of adding a fragment to a queue.
+enum InsertResult {
+    /// The fragment is added.
+    Added,
+    /// The fragment contains only bytes that have been received, so it is ignored.
+    Duplicate,
+    /// The fragment is inconsistent with the previous fragments, so the queue is discarded.
+    Invalid,
+}
+
+impl Reassembler {
+    pub(super) const fn new() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+            buffered_len: 0,
+        }
+    }
+
+    /// Processes an incoming IPv4 fragment.
+    ///
+    /// If the fragment completes a packet, the reassembled packet is written to `buffer` and
+    /// returned.
+    pub(super) fn process<'a>(
+        &mut self,
+        pkt: &Ipv4Packet<&[u8]>,
+        now: Instant,
+        buffer: &'a mut Vec<u8>,
+    ) -> Option<Ipv4Packet<&'a [u8]>> {
+        self.remove_expired(now);
+
+        let key = FragKey {
+            src_addr: pkt.src_addr(),
+            dst_addr: pkt.dst_addr(),
+            ident: pkt.ident(),
+            protocol: u8::from(pkt.next_header()),
+        };
+        let offset = pkt.frag_offset() as usize;
+        let payload = pkt.payload();
+        let header = &pkt.as_ref()[..pkt.header_len() as usize];
+
+        // All fragments except the last one must carry a multiple of eight bytes, and the
+        // reassembled packet must not exceed the maximum length.
+        if (pkt.more_frags() && (payload.is_empty() || payload.len() % FRAG_OFFSET_UNIT != 0))
+            || header.len() + offset + payload.len() > MAX_PACKET_LEN
+        {
+            return None;
+        }
+
+        let old_len = self.queues.get(&key).map_or(0, |queue| queue.payload.len());
+        if self.buffered_len - old_len + (offset + payload.len()).max(old_len) > MAX_BUFFERED_LEN {
+            return None;
+        }
+
+        let queue = match self.queues.get_mut(&key) {
+            Some(queue) => queue,
+            None => {
+                if self.queues.len() >= MAX_QUEUES {
+                    return None;
+                }
+                self.queues.entry(key).or_insert(FragQueue {
+                    header: None,
+                    payload: Vec::new(),
+                    received: Vec::new(),
+                    total_len: None,
+                    expires_at: now + REASSEMBLY_TIMEOUT,
+                })
+            }
+        };
+
+        match queue.insert(offset, payload, pkt.more_frags()) {
+            InsertResult::Added => (),
+            InsertResult::Duplicate => return None,
+            InsertResult::Invalid => {
+                self.remove_queue(&key);
+                return None;
+            }
+        }
+        if offset == 0 {
+            queue.header = Some(header.to_vec());
+        }
+        self.buffered_len = self.buffered_len - old_len + queue.payload.len();
+
+        if !queue.is_complete() {
+            return None;
+        }
+
+        let queue = self.remove_queue(&key)?;
+        queue.reassemble(buffer);
+        Ipv4Packet::new_checked(&buffer[..]).ok()
+    }
+
+    /// Removes the queues whose fragments have been waiting for too long.
+    //
+    // TODO: Send the ICMP "Fragment Reassembly Time Exceeded" message if the first fragment has
+    // been received.
+    fn remove_expired(&mut self, now: Instant) {
+        let mut removed_len = 0;
+        self.queues.retain(|_, queue| {
+            let is_expired = queue.expires_at <= now;
+            if is_expired {
+                removed_len += queue.payload.len();
+            }
+            !is_expired
+        });
+        self.buffered_len -= removed_len;
+    }
+
+    fn remove_queue(&mut self, key: &FragKey) -> Option<FragQueue> {
+        let queue = self.queues.remove(key)?;
+        self.buffered_len -= queue.payload.len();
+        Some(queue)
+    }
+}
+
+impl FragQueue {
+    fn insert(&mut self, offset: usize, data: &[u8], more_frags: bool) -> InsertResult {
+        let end = offset + data.len();
+
+        // The end of the packet is known once the last fragment is received, and it must not
+        // change afterwards.
+        match (self.total_len, more_frags) {
+            (Some(total_len), true) if end > total_len => return InsertResult::Invalid,
+            (Some(total_len), false) if end != total_len => return InsertResult::Invalid,
+            (None, false) if self.received.last().is_some_and(|last| last.1 > end) => {
+                return InsertResult::Invalid
+            }
+            _ => (),
+        }
+
+        // Like Linux, overlapping fragments invalidate the whole packet, because they can be
+        // used to evade the inspection of packet filters. However, exact duplicates may be
+        // caused by retransmission, so they are ignored.
+        //
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc5722> and
+        // <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/inet_fragment.c#L459>.
+        for &(start_received, end_received) in self.received.iter() {
+            if start_received <= offset && end <= end_received {
+                return InsertResult::Duplicate;
+            }
+            if offset < end_received && start_received < end {
+                return InsertResult::Invalid;
+            }
+        }
+
+        if !more_frags {
+            self.total_len = Some(end);
+        }
+
+        if self.payload.len() < end {
+            self.payload.resize(end, 0);
+        }
+        self.payload[offset..end].copy_from_slice(data);
+
+        let pos = self
+            .received
+            .iter()
+            .position(|range| range.0 > offset)
+            .unwrap_or(self.received.len());
+        self.received.insert(pos, (offset, end));
+        self.received.dedup_by(|next, prev| {
+            if prev.1 != next.0 {
+                return false;
+            }
+            prev.1 = next.1;
+            true
+        });
+
+        InsertResult::Added
+    }
+
+    fn is_complete(&self) -> bool {
+        self.header.is_some()
+            && self
+                .total_len
+                .is_some_and(|total_len| self.received.as_slice() == [(0, total_len)])
+    }
+
+    /// Writes the reassembled packet to the buffer.
+    fn reassemble(self, buffer: &mut Vec<u8>) {
+        let header = self.header.unwrap();
+        let header_len = header.len();
+
+        buffer.clear();
+        buffer.extend_from_slice(&header);
+        buffer.extend_from_slice(&self.payload);
+
+        let mut pkt = Ipv4Packet::new_unchecked(&mut buffer[..]);
+        pkt.set_total_len((header_len + self.payload.len()) as u16);
+        pkt.set_more_frags(false);
+        pkt.set_frag_offset(0);
+        pkt.fill_checksum();
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing of ICMP error messages.
+//!
+//! An ICMP error message includes the IP header and the beginning of the transport header of the
+//! undeliverable packet, which identify the socket that has sent the packet.
+
+use alloc::vec::Vec;
+
+use smoltcp::{
+    phy::ChecksumCapabilities,
+    wire::{
+        Icmpv4Packet, Icmpv6Packet, IpAddress, IpEndpoint, IpProtocol, Ipv4Packet, Ipv4Repr,
+        Ipv6Packet, Ipv6Repr, IPV4_HEADER_LEN, IPV6_HEADER_LEN,
+    },
+};
+
+use crate::socket::{
+    IcmpError, ICMPV4_DEST_UNREACH, ICMPV4_PARAMETER_PROBLEM, ICMPV4_TIME_EXCEEDED,
+    ICMPV6_DEST_UNREACH, ICMPV6_PARAMETER_PROBLEM, ICMPV6_PKT_TOOBIG, ICMPV6_TIME_EXCEEDED,
+};
+
+/// An ICMP error message that is received by the iface.
+pub(super) struct ReceivedIcmpError {
+    /// The transport protocol of the undeliverable packet.
+    pub(super) protocol: IpProtocol,
+    /// The source of the undeliverable packet, which is the local endpoint of the socket.
+    ///
+    /// For ICMP sockets, the port is the identifier of the echo request.
+    pub(super) local_endpoint: IpEndpoint,
+    /// The error that should be reported to the socket.
+    pub(super) error: IcmpError,
+}
+
+/// The length of the ICMP header of ICMP error messages.
+const ICMP_HEADER_LEN: usize = 8;
+
+/// The length of the transport header included in ICMP error messages.
+///
+/// RFC 792 requires that the first 64 bits of the data of the undeliverable packet are included.
+/// See <https://datatracker.ietf.org/doc/html/rfc792>.
+const TRANSPORT_HEADER_LEN: usize = 8;
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+
+/// The number of valid codes of the "Destination Unreachable" ICMPv4 messages.
+const NR_ICMPV4_UNREACH_CODES: u8 = 16;
+
+/// Returns whether the ICMPv4 message is an error message that should be reported to sockets.
+pub(super) fn is_icmpv4_error(icmp_pkt: &Icmpv4Packet<&[u8]>) -> bool {
+    matches!(
+        icmp_pkt.as_ref()[0],
+        ICMPV4_DEST_UNREACH | ICMPV4_TIME_EXCEEDED | ICMPV4_PARAMETER_PROBLEM
+    )
+}
+
+/// Returns whether the ICMPv6 message is an error message that should be reported to sockets.
+pub(super) fn is_icmpv6_error(icmp_pkt: &Icmpv6Packet<&[u8]>) -> bool {
+    matches!(
+        icmp_pkt.as_ref()[0],
+        ICMPV6_DEST_UNREACH | ICMPV6_PKT_TOOBIG | ICMPV6_TIME_EXCEEDED | ICMPV6_PARAMETER_PROBLEM
+    )
+}
+
+/// Parses an ICMPv4 error message.
+///
+/// Note that the undeliverable packet included in the message is usually truncated, so it cannot
+/// be parsed by [`smoltcp::wire::Icmpv4Repr::parse`].
+pub(super) fn parse_icmpv4_error(
+    ipv4_repr: &Ipv4Repr,
+    icmp_pkt: &Icmpv4Packet<&[u8]>,
+    checksum_caps: &ChecksumCapabilities,
+) -> Option<ReceivedIcmpError> {
+    if checksum_caps.icmpv4.rx() && !icmp_pkt.verify_checksum() {
+        return None;
+    }
+
+    let message = icmp_pkt.as_ref();
+    let header = message.get(..ICMP_HEADER_LEN)?;
+    let (type_, code) = (header[0], header[1]);
+    if type_ == ICMPV4_DEST_UNREACH && code >= NR_ICMPV4_UNREACH_CODES {
+        return None;
+    }
+
+    let inner = &message[ICMP_HEADER_LEN..];
+    if inner.len() < IPV4_HEADER_LEN {
+        return None;
+    }
+    let inner_pkt = Ipv4Packet::new_unchecked(inner);
+    if inner_pkt.version() != 4 {
+        return None;
+    }
+    let transport = inner.get(inner_pkt.header_len() as usize..)?;
+
+    let mut error = new_icmp_error(
+        false,
+        type_,
+        code,
+        IpAddress::Ipv4(ipv4_repr.src_addr),
+        IpAddress::Ipv4(inner_pkt.dst_addr()),
+    );
+    if error.is_too_big() {
+        // The MTU of the next hop is stored in the last 16 bits of the ICMP header. See
+        // <https://datatracker.ietf.org/doc/html/rfc1191#section-4>.
+        error.mtu = u16::from_be_bytes([header[6], header[7]]) as u32;
+    }
+
+    parse_transport(
+        error,
+        inner_pkt.next_header(),
+        IpAddress::Ipv4(inner_pkt.src_addr()),
+        transport,
+    )
+}
+
+/// Parses an ICMPv6 error message.
+///
+/// Note that the undeliverable packet included in the message is usually truncated, so it cannot
+/// be parsed by [`smoltcp::wire::Icmpv6Repr::parse`].
+pub(super) fn parse_icmpv6_error(
+    ipv6_repr: &Ipv6Repr,
+    icmp_pkt: &Icmpv6Packet<&[u8]>,
+    checksum_caps: &ChecksumCapabilities,
+) -> Option<ReceivedIcmpError> {
+    if checksum_caps.icmpv6.rx()
+        && !icmp_pkt.verify_checksum(&ipv6_repr.src_addr, &ipv6_repr.dst_addr)
+    {
+        return None;
+    }
+
+    let message = icmp_pkt.as_ref();
+    let header = message.get(..ICMP_HEADER_LEN)?;
+    let (type_, code) = (header[0], header[1]);
+
+    // TODO: Support IPv6 extension headers in the undeliverable packet.
+    let inner = &message[ICMP_HEADER_LEN..];
+    if inner.len() < IPV6_HEADER_LEN {
+        return None;
+    }
+    let inner_pkt = Ipv6Packet::new_unchecked(inner);
+    if inner_pkt.version() != 6 {
+        return None;
+    }
+    let transport = &inner[IPV6_HEADER_LEN..];
+
+    let mut error = new_icmp_error(
+        true,
+        type_,
+        code,
+        IpAddress::Ipv6(ipv6_repr.src_addr),
+        IpAddress::Ipv6(inner_pkt.dst_addr()),
+    );
+    if error.is_too_big() {
+        // The MTU of the next hop is stored in the last 32 bits of the ICMP header. See
+        // <https://datatracker.ietf.org/doc/html/rfc4443#section-3.2>.
+        error.mtu = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    }
+
+    parse_transport(
+        error,
+        inner_pkt.next_header(),
+        IpAddress::Ipv6(inner_pkt.src_addr()),
+        transport,
+    )
+}
+
+fn new_icmp_error(
+    is_ipv6: bool,
+    type_: u8,
+    code: u8,
+    offender: IpAddress,
+    remote_addr: IpAddress,
+) -> IcmpError {
+    IcmpError {
+        is_ipv6,
+        type_,
+        code,
+        mtu: 0,
+        offender,
+        remote_endpoint: IpEndpoint::new(remote_addr, 0),
+        payload: Vec::new(),
+    }
+}
+
+/// Parses the transport header of the undeliverable packet to find its source and destination.
+fn parse_transport(
+    mut error: IcmpError,
+    protocol: IpProtocol,
+    local_addr: IpAddress,
+    transport: &[u8],
+) -> Option<ReceivedIcmpError> {
+    let header = transport.get(..TRANSPORT_HEADER_LEN)?;
+
+    let local_port = match protocol {
+        // Both TCP and UDP headers start with the source port and the destination port.
+        IpProtocol::Tcp | IpProtocol::Udp => {
+            error.remote_endpoint.port = u16::from_be_bytes([header[2], header[3]]);
+            if protocol == IpProtocol::Udp {
+                error.payload = transport[TRANSPORT_HEADER_LEN..].to_vec();
+            }
+            u16::from_be_bytes([header[0], header[1]])
+        }
+        // The identifier of an echo request follows the type, the code, and the checksum.
+        IpProtocol::Icmp if header[0] == ICMPV4_ECHO_REQUEST => {
+            error.payload = transport.to_vec();
+            u16::from_be_bytes([header[4], header[5]])
+        }
+        IpProtocol::Icmpv6 if header[0] == ICMPV6_ECHO_REQUEST => {
+            error.payload = transport.to_vec();
+            u16::from_be_bytes([header[4], header[5]])
+        }
+        _ => return None,
+    };
+
+    Some(ReceivedIcmpError {
+        protocol,
+        local_endpoint: IpEndpoint::new(local_addr, local_port),
+        error,
+    })
+}
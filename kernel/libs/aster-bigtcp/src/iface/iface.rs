@@ -1,10 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 
-use smoltcp::wire::Ipv4Address;
+use smoltcp::{
+    iface::Route,
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
+};
 
-use super::{port::BindPortConfig, BoundPort, InterfaceFlags, InterfaceType};
+use super::{port::BindPortConfig, BoundPort, InterfaceFlags, InterfaceType, RxHandler};
 use crate::{errors::BindError, ext::Ext};
 
 /// A network interface.
@@ -19,13 +22,26 @@ pub trait Iface<E>: internal::IfaceInternal<E> + Send + Sync {
 
     /// Returns the maximum transmission unit.
     fn mtu(&self) -> usize;
+
+    /// Returns the Ethernet address.
+    ///
+    /// This method returns `None` if the iface does not use Ethernet (e.g., the loopback iface),
+    /// in which case the frames sent and received by the iface start with the IP headers.
+    fn ether_addr(&self) -> Option<EthernetAddress>;
+
+    /// Sends a raw frame through the device.
+    ///
+    /// The frame is sent as is, bypassing the network stack. This method returns `false` if the
+    /// device is busy and cannot send the frame.
+    fn send_frame(&self, frame: &[u8]) -> bool;
 }
 
 impl<E: Ext> dyn Iface<E> {
     /// Binds a socket to the iface.
     ///
     /// After binding the socket to the iface, the iface will handle all packets to and from the
-    /// socket.
+    /// socket. `addr` is the local address of the socket, which should be one of the addresses
+    /// of the iface.
     ///
     /// If [`BindPortConfig::Ephemeral`] is specified, the iface will pick up an ephemeral port for
     /// the socket.
@@ -35,10 +51,11 @@ impl<E: Ext> dyn Iface<E> {
     /// <https://github.com/smoltcp-rs/smoltcp/issues/779>.
     pub fn bind(
         self: &Arc<Self>,
+        addr: IpAddress,
         config: BindPortConfig,
     ) -> core::result::Result<BoundPort<E>, BindError> {
         let common = self.common();
-        common.bind(self.clone(), config)
+        common.bind(self.clone(), addr, config)
     }
 
     /// Returns the interface index.
@@ -78,6 +95,80 @@ impl<E: Ext> dyn Iface<E> {
         self.common().prefix_len()
     }
 
+    /// Returns the IPv4 addresses of the iface.
+    pub fn ipv4_addrs(&self) -> Vec<Ipv4Cidr> {
+        self.common().ipv4_addrs()
+    }
+
+    /// Returns the IPv6 addresses of the iface.
+    pub fn ipv6_addrs(&self) -> Vec<Ipv6Cidr> {
+        self.common().ipv6_addrs()
+    }
+
+    /// Selects one of the IPv6 addresses of the iface to send packets to `dst_addr`.
+    ///
+    /// This method returns `None` if the iface has no suitable IPv6 addresses.
+    pub fn ipv6_src_addr(&self, dst_addr: &Ipv6Address) -> Option<Ipv6Address> {
+        self.common().ipv6_src_addr(dst_addr)
+    }
+
+    /// Adds a static IPv6 address to the iface.
+    ///
+    /// This method returns `false` if there are too many addresses on the iface.
+    pub fn add_ipv6_addr(&self, ipv6_cidr: Ipv6Cidr) -> bool {
+        self.common().add_ipv6_addr(ipv6_cidr)
+    }
+
+    /// Adds a static IPv4 address to the iface.
+    ///
+    /// This method returns `false` if there are too many addresses on the iface.
+    pub fn add_ipv4_addr(&self, ipv4_cidr: Ipv4Cidr) -> bool {
+        self.common().add_ipv4_addr(ipv4_cidr)
+    }
+
+    /// Removes an IPv4 or IPv6 address from the iface.
+    ///
+    /// This method returns `false` if the iface does not have the address.
+    pub fn remove_ip_addr(&self, ip_cidr: IpCidr) -> bool {
+        self.common().remove_ip_addr(ip_cidr)
+    }
+
+    /// Returns the routes via gateways of the iface.
+    ///
+    /// Routes to the subnets of the iface addresses are implicit and not included.
+    pub fn routes(&self) -> Vec<Route> {
+        self.common().routes()
+    }
+
+    /// Adds a route via a gateway to the iface.
+    ///
+    /// An existing route to the same destination will be replaced. This method returns `false`
+    /// if there are too many routes on the iface.
+    pub fn add_route(&self, route: Route) -> bool {
+        self.common().add_route(route)
+    }
+
+    /// Removes the route to the destination from the iface.
+    ///
+    /// This method returns `false` if the iface does not have such a route.
+    pub fn remove_route(&self, cidr: &IpCidr) -> bool {
+        self.common().remove_route(cidr)
+    }
+
+    /// Registers a handler that takes over the frames received by the iface.
+    ///
+    /// This method returns `false` if the iface already has a handler.
+    pub fn register_rx_handler(&self, handler: Arc<dyn RxHandler>) -> bool {
+        self.common().register_rx_handler(handler)
+    }
+
+    /// Unregisters the handler of the received frames.
+    ///
+    /// This method returns the unregistered handler, or `None` if the iface has no handler.
+    pub fn unregister_rx_handler(&self) -> Option<Arc<dyn RxHandler>> {
+        self.common().unregister_rx_handler()
+    }
+
     /// Returns a reference to the associated [`ScheduleNextPoll`].
     pub fn sched_poll(&self) -> &E::ScheduleNextPoll {
         self.common().sched_poll()
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Utilities for IPv6 addresses.
+
+use smoltcp::wire::{EthernetAddress, Ipv6Address, Ipv6Cidr};
+
+/// The link-local all-nodes multicast address.
+pub(super) const ALL_NODES: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// The link-local all-routers multicast address.
+pub(super) const ALL_ROUTERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// The link-local prefix (i.e., `fe80::/64`).
+pub(super) const LINK_LOCAL_PREFIX: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+
+/// The prefix length of the addresses that are generated from interface identifiers.
+pub(super) const EUI64_PREFIX_LEN: u8 = 64;
+
+/// Returns whether the address is a link-local unicast address (i.e., in `fe80::/10`).
+pub(super) fn is_link_local(addr: &Ipv6Address) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Selects a source address from `addrs` to send packets to `dst_addr`.
+///
+/// This is a simplified version of the rules in RFC 6724: The destination address itself is
+/// preferred, and then an address with the same scope (link-local or not).
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc6724#section-5>.
+pub(super) fn select_src_addr(addrs: &[Ipv6Cidr], dst_addr: &Ipv6Address) -> Option<Ipv6Address> {
+    let candidates = || addrs.iter().map(Ipv6Cidr::address);
+
+    candidates()
+        .find(|addr| addr == dst_addr)
+        .or_else(|| {
+            candidates()
+                .find(|addr| !addr.is_loopback() && is_link_local(addr) == is_link_local(dst_addr))
+        })
+        .or_else(|| candidates().find(|addr| !addr.is_loopback()))
+}
+
+/// Generates an address from a 64-bit prefix and the modified EUI-64 interface identifier of an
+/// Ethernet address.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc4291#appendix-A>.
+pub(super) fn eui64_addr(prefix: &Ipv6Address, ether_addr: &EthernetAddress) -> Ipv6Address {
+    let mac = ether_addr.as_bytes();
+
+    let mut bytes = prefix.octets();
+    bytes[8] = mac[0] ^ 0x02;
+    bytes[9] = mac[1];
+    bytes[10] = mac[2];
+    bytes[11] = 0xff;
+    bytes[12] = 0xfe;
+    bytes[13..].copy_from_slice(&mac[3..]);
+
+    Ipv6Address::from(bytes)
+}
+
+/// Returns the solicited-node multicast address of an address.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc4291#section-2.7.1>.
+pub(super) fn solicited_node(addr: &Ipv6Address) -> Ipv6Address {
+    let bytes = addr.octets();
+
+    Ipv6Address::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | bytes[13] as u16,
+        u16::from_be_bytes([bytes[14], bytes[15]]),
+    )
+}
+
+/// Returns the Ethernet address to which an IPv6 multicast address is mapped.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2464#section-7>.
+pub(super) fn multicast_ether_addr(addr: &Ipv6Address) -> EthernetAddress {
+    let bytes = addr.octets();
+
+    EthernetAddress([0x33, 0x33, bytes[12], bytes[13], bytes[14], bytes[15]])
+}
@@ -1,18 +1,28 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod common;
+mod filter;
+mod frag;
+mod icmp;
 #[expect(clippy::module_inception)]
 mod iface;
+mod ipv6;
 mod phy;
 mod poll;
 mod poll_iface;
 mod port;
+mod rx_handler;
 mod sched;
+mod tap;
 mod time;
 
 pub use common::{BoundPort, InterfaceFlags, InterfaceType};
+pub use filter::{FilterHook, FilterVerdict, IcmpUnreachable, PacketFilter, RejectWith};
 pub use iface::Iface;
 pub use phy::{EtherIface, IpIface};
 pub(crate) use poll_iface::{PollKey, PollableIfaceMut};
 pub use port::BindPortConfig;
+pub use rx_handler::RxHandler;
 pub use sched::ScheduleNextPoll;
+pub use smoltcp::iface::Route;
+pub use tap::{FrameDirection, FrameTap};
@@ -1,15 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::SpinLock;
 use smoltcp::{
-    iface::{packet::Packet, Config, Context},
+    iface::{
+        packet::{IpPayload, Packet},
+        Config, Context,
+    },
     phy::{Device, DeviceCapabilities, TxToken},
+    time::Duration,
     wire::{
         self, ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
-        EthernetRepr, IpAddress, Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Packet,
+        EthernetRepr, Icmpv6Packet, Icmpv6Repr, IpAddress, IpProtocol, Ipv4Address, Ipv4AddressExt,
+        Ipv4Cidr, Ipv4Packet, Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags,
+        NdiscPrefixInformation, NdiscRepr, RawHardwareAddress,
     },
 };
 
@@ -18,7 +25,12 @@ use crate::{
     ext::Ext,
     iface::{
         common::{IfaceCommon, InterfaceType},
+        filter::OutgoingPacket,
         iface::internal::IfaceInternal,
+        ipv6::{self, ALL_ROUTERS, EUI64_PREFIX_LEN, LINK_LOCAL_PREFIX},
+        poll::IpPacket,
+        rx_handler::DivertedFrames,
+        tap::TapDevice,
         time::get_network_timestamp,
         Iface, InterfaceFlags, ScheduleNextPoll,
     },
@@ -28,19 +40,43 @@ pub struct EtherIface<D, E: Ext> {
     driver: D,
     common: IfaceCommon<E>,
     ether_addr: EthernetAddress,
+    link_local_addr: Ipv6Address,
     arp_table: SpinLock<BTreeMap<Ipv4Address, EthernetAddress>, BottomHalfDisabled>,
+    ndisc_table: SpinLock<BTreeMap<Ipv6Address, EthernetAddress>, BottomHalfDisabled>,
+    pending_slaac: SpinLock<PendingSlaac, BottomHalfDisabled>,
+    has_solicited_routers: AtomicBool,
+}
+
+/// The configurations learned from Router Advertisement messages, which are yet to be applied.
+///
+/// They cannot be applied immediately because the iface is locked when the messages are
+/// processed.
+#[derive(Default)]
+struct PendingSlaac {
+    addr: Option<Ipv6Cidr>,
+    gateway: Option<Ipv6Address>,
+}
+
+/// A packet that is sent to resolve the link-layer address of the next hop.
+enum NeighborRequest {
+    Arp(ArpRepr),
+    Ndisc(EthernetRepr, Packet<'static>),
 }
 
 impl<D: WithDevice, E: Ext> EtherIface<D, E> {
     pub fn new(
         driver: D,
         ether_addr: EthernetAddress,
-        ip_cidr: Ipv4Cidr,
-        gateway: Ipv4Address,
+        ip_cidr: Option<Ipv4Cidr>,
+        gateway: Option<Ipv4Address>,
         name: String,
         sched_poll: E::ScheduleNextPoll,
         flags: InterfaceFlags,
     ) -> Arc<Self> {
+        // TODO: Perform Duplicate Address Detection before using the link-local address. See
+        // <https://datatracker.ietf.org/doc/html/rfc4862#section-5.4>.
+        let link_local_addr = ipv6::eui64_addr(&LINK_LOCAL_PREFIX, &ether_addr);
+
         let interface = driver.with(|device| {
             let config = Config::new(wire::HardwareAddress::Ethernet(ether_addr));
             let now = get_network_timestamp();
@@ -48,12 +84,22 @@ impl<D: WithDevice, E: Ext> EtherIface<D, E> {
             let mut interface = smoltcp::iface::Interface::new(config, device, now);
             interface.update_ip_addrs(|ip_addrs| {
                 debug_assert!(ip_addrs.is_empty());
-                ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                if let Some(ip_cidr) = ip_cidr {
+                    ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                }
+                ip_addrs
+                    .push(wire::IpCidr::Ipv6(Ipv6Cidr::new(
+                        link_local_addr,
+                        EUI64_PREFIX_LEN,
+                    )))
+                    .unwrap();
             });
-            interface
-                .routes_mut()
-                .add_default_ipv4_route(gateway)
-                .unwrap();
+            if let Some(gateway) = gateway {
+                interface
+                    .routes_mut()
+                    .add_default_ipv4_route(gateway)
+                    .unwrap();
+            }
             interface
         });
 
@@ -63,7 +109,11 @@ impl<D: WithDevice, E: Ext> EtherIface<D, E> {
             driver,
             common,
             ether_addr,
+            link_local_addr,
             arp_table: SpinLock::new(BTreeMap::new()),
+            ndisc_table: SpinLock::new(BTreeMap::new()),
+            pending_slaac: SpinLock::new(PendingSlaac::default()),
+            has_solicited_routers: AtomicBool::new(false),
         })
     }
 }
@@ -79,21 +129,108 @@ where
     D::Device: NotifyDevice,
 {
     fn poll(&self) {
-        self.driver.with(|device| {
+        let diverted_frames = self.driver.with(|device| {
+            let mut tap_device =
+                TapDevice::<_, E::FrameTap>::new(&mut *device, self.common.index());
+
+            let diverted_frames = self
+                .common
+                .rx_handler()
+                .map(|handler| DivertedFrames::take_from(handler, &mut tap_device));
+
+            self.solicit_routers(&mut tap_device);
+
             let next_poll = self.common.poll(
-                &mut *device,
+                &mut tap_device,
                 |data, iface_cx, tx_token| self.process(data, iface_cx, tx_token),
                 |pkt, iface_cx, tx_token| self.dispatch(pkt, iface_cx, tx_token),
             );
             device.notify_poll_end();
             self.common.sched_poll().schedule_next_poll(next_poll);
+
+            diverted_frames
         });
+
+        self.apply_slaac();
+
+        if let Some(diverted_frames) = diverted_frames {
+            diverted_frames.deliver(self.common.index());
+        }
     }
 
     fn mtu(&self) -> usize {
         self.driver
             .with(|device| device.capabilities().max_transmission_unit)
     }
+
+    fn ether_addr(&self) -> Option<EthernetAddress> {
+        Some(self.ether_addr)
+    }
+
+    fn send_frame(&self, frame: &[u8]) -> bool {
+        self.driver.with(|device| {
+            let mut tap_device =
+                TapDevice::<_, E::FrameTap>::new(&mut *device, self.common.index());
+
+            let Some(tx_token) = tap_device.transmit(get_network_timestamp()) else {
+                return false;
+            };
+            tx_token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+
+            device.notify_poll_end();
+            true
+        })
+    }
+}
+
+impl<D: WithDevice, E: Ext> EtherIface<D, E> {
+    /// Sends a Router Solicitation message if it has not been sent.
+    ///
+    /// The routers will reply with Router Advertisement messages, which carry the prefixes for
+    /// Stateless Address Autoconfiguration (SLAAC) and the default gateway.
+    ///
+    /// Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-6.3.7>.
+    fn solicit_routers<T: Device + ?Sized>(&self, device: &mut T) {
+        if self.has_solicited_routers.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let Some(tx_token) = device.transmit(get_network_timestamp()) else {
+            return;
+        };
+        self.has_solicited_routers.store(true, Ordering::Relaxed);
+
+        let solicit_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+            lladdr: Some(RawHardwareAddress::from_bytes(self.ether_addr.as_bytes())),
+        });
+        let pkt = Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: self.link_local_addr,
+                dst_addr: ALL_ROUTERS,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: solicit_repr.buffer_len(),
+                hop_limit: 255,
+            },
+            IpPayload::Icmpv6(solicit_repr),
+        );
+
+        let mut interface = self.common.interface();
+        self.dispatch(&pkt, interface.context_mut(), tx_token);
+    }
+}
+
+impl<D, E: Ext> EtherIface<D, E> {
+    /// Applies the configurations learned from Router Advertisement messages.
+    fn apply_slaac(&self) {
+        let PendingSlaac { addr, gateway } = core::mem::take(&mut *self.pending_slaac.lock());
+
+        if let Some(addr) = addr {
+            self.common.add_ipv6_addr(addr);
+        }
+        if let Some(gateway) = gateway {
+            self.common.set_ipv6_gateway(gateway);
+        }
+    }
 }
 
 impl<D, E: Ext> EtherIface<D, E> {
@@ -102,7 +239,7 @@ impl<D, E: Ext> EtherIface<D, E> {
         data: &'pkt [u8],
         iface_cx: &mut Context,
         tx_token: T,
-    ) -> Option<(Ipv4Packet<&'pkt [u8]>, T)> {
+    ) -> Option<(IpPacket<&'pkt [u8]>, T)> {
         match self.parse_ip_or_process_arp(data, iface_cx) {
             Ok(pkt) => Some((pkt, tx_token)),
             Err(Some(arp)) => {
@@ -117,20 +254,28 @@ impl<D, E: Ext> EtherIface<D, E> {
         &self,
         data: &'pkt [u8],
         iface_cx: &mut Context,
-    ) -> Result<Ipv4Packet<&'pkt [u8]>, Option<ArpRepr>> {
+    ) -> Result<IpPacket<&'pkt [u8]>, Option<ArpRepr>> {
         // Parse the Ethernet header. Ignore the packet if the header is ill-formed.
         let frame = EthernetFrame::new_checked(data).map_err(|_| None)?;
         let repr = EthernetRepr::parse(&frame).map_err(|_| None)?;
 
-        // Ignore the Ethernet frame if it is not sent to us.
-        if !repr.dst_addr.is_broadcast() && repr.dst_addr != self.ether_addr {
+        // Ignore the Ethernet frame if it is not sent to us. Note that broadcast addresses are
+        // also multicast addresses.
+        //
+        // TODO: Filter multicast frames according to the multicast groups that we have joined.
+        if !repr.dst_addr.is_multicast() && repr.dst_addr != self.ether_addr {
             return Err(None);
         }
 
         // Ignore the Ethernet frame if the protocol is not supported.
         match repr.ethertype {
-            EthernetProtocol::Ipv4 => {
-                Ok(Ipv4Packet::new_checked(frame.payload()).map_err(|_| None)?)
+            EthernetProtocol::Ipv4 => Ok(IpPacket::Ipv4(
+                Ipv4Packet::new_checked(frame.payload()).map_err(|_| None)?,
+            )),
+            EthernetProtocol::Ipv6 => {
+                let pkt = Ipv6Packet::new_checked(frame.payload()).map_err(|_| None)?;
+                self.snoop_ndisc(&pkt, iface_cx);
+                Ok(IpPacket::Ipv6(pkt))
             }
             EthernetProtocol::Arp => {
                 let pkt = ArpPacket::new_checked(frame.payload()).map_err(|_| None)?;
@@ -197,71 +342,252 @@ impl<D, E: Ext> EtherIface<D, E> {
         }
     }
 
+    /// Learns the link-layer addresses and the configurations of routers from Neighbor Discovery
+    /// messages.
+    ///
+    /// The messages will still be passed to the IP layer, which will reply to them if necessary.
+    fn snoop_ndisc(&self, pkt: &Ipv6Packet<&[u8]>, iface_cx: &Context) {
+        let Ok(ipv6_repr) = Ipv6Repr::parse(pkt) else {
+            return;
+        };
+
+        // Neighbor Discovery messages must not be forwarded by routers. See
+        // <https://datatracker.ietf.org/doc/html/rfc4861#section-6.1.2>.
+        if ipv6_repr.next_header != IpProtocol::Icmpv6 || ipv6_repr.hop_limit != 255 {
+            return;
+        }
+
+        let Ok(icmp_pkt) = Icmpv6Packet::new_checked(pkt.payload()) else {
+            return;
+        };
+        let Ok(Icmpv6Repr::Ndisc(ndisc_repr)) = Icmpv6Repr::parse(
+            &ipv6_repr.src_addr,
+            &ipv6_repr.dst_addr,
+            &icmp_pkt,
+            &iface_cx.checksum_caps(),
+        ) else {
+            return;
+        };
+
+        match ndisc_repr {
+            NdiscRepr::NeighborSolicit { lladdr, .. } => {
+                self.learn_neighbor(&ipv6_repr.src_addr, lladdr)
+            }
+            NdiscRepr::NeighborAdvert {
+                target_addr,
+                lladdr,
+                ..
+            } => self.learn_neighbor(&target_addr, lladdr),
+            NdiscRepr::RouterAdvert {
+                router_lifetime,
+                lladdr,
+                prefix_info,
+                ..
+            } => {
+                self.learn_neighbor(&ipv6_repr.src_addr, lladdr);
+                self.learn_router(&ipv6_repr.src_addr, router_lifetime, prefix_info.as_ref());
+            }
+            _ => (),
+        }
+    }
+
+    fn learn_neighbor(&self, ipv6_addr: &Ipv6Address, lladdr: Option<RawHardwareAddress>) {
+        // Ignore the message if the source addresses are not unicast.
+        if ipv6_addr.is_unspecified() || ipv6_addr.is_multicast() {
+            return;
+        }
+        let Some(lladdr) = lladdr else {
+            return;
+        };
+        if lladdr.len() != 6 {
+            return;
+        }
+        let ether_addr = EthernetAddress::from_bytes(lladdr.as_bytes());
+        if !ether_addr.is_unicast() {
+            return;
+        }
+
+        // Insert the mapping between the Ethernet address and the IP address.
+        //
+        // TODO: Remove the mapping if it expires.
+        self.ndisc_table.lock().insert(*ipv6_addr, ether_addr);
+    }
+
+    fn learn_router(
+        &self,
+        router_addr: &Ipv6Address,
+        router_lifetime: Duration,
+        prefix_info: Option<&NdiscPrefixInformation>,
+    ) {
+        let mut pending_slaac = self.pending_slaac.lock();
+
+        // A zero lifetime indicates that the router is not a default router. See
+        // <https://datatracker.ietf.org/doc/html/rfc4861#section-4.2>.
+        if router_lifetime.total_millis() != 0 {
+            pending_slaac.gateway = Some(*router_addr);
+        }
+
+        let Some(prefix_info) = prefix_info else {
+            return;
+        };
+
+        // Only 64-bit prefixes can be combined with the EUI-64 interface identifiers. See
+        // <https://datatracker.ietf.org/doc/html/rfc4862#section-5.5.3>.
+        //
+        // TODO: Remove the address if its lifetime expires.
+        if prefix_info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            && prefix_info.prefix_len == EUI64_PREFIX_LEN
+            && prefix_info.valid_lifetime.total_millis() != 0
+            && !ipv6::is_link_local(&prefix_info.prefix)
+        {
+            let addr = ipv6::eui64_addr(&prefix_info.prefix, &self.ether_addr);
+            pending_slaac.addr = Some(Ipv6Cidr::new(addr, EUI64_PREFIX_LEN));
+        }
+    }
+
     fn dispatch<T: TxToken>(&self, pkt: &Packet, iface_cx: &mut Context, tx_token: T) {
-        match self.resolve_ether_or_generate_arp(pkt, iface_cx) {
-            Ok(ether) => Self::emit_ip(&ether, pkt, &iface_cx.caps, tx_token),
-            Err(Some(arp)) => Self::emit_arp(&arp, tx_token),
+        let Some(pkt) = self.common.filter_egress(pkt, iface_cx) else {
+            return;
+        };
+
+        // The filter may have changed the destination address, so the next hop must be resolved
+        // after the packet is filtered.
+        match self.resolve_ether_or_generate_request(pkt.dst_addr(), iface_cx) {
+            Ok(ether) => self.emit_ip(&ether, &pkt, &iface_cx.caps, tx_token),
+            Err(Some(NeighborRequest::Arp(arp))) => Self::emit_arp(&arp, tx_token),
+            Err(Some(NeighborRequest::Ndisc(ether, solicit))) => self.emit_ip(
+                &ether,
+                &OutgoingPacket::Repr(&solicit),
+                &iface_cx.caps,
+                tx_token,
+            ),
             Err(None) => (),
         }
     }
 
-    fn resolve_ether_or_generate_arp(
+    fn resolve_ether_or_generate_request(
         &self,
-        pkt: &Packet,
+        dst_addr: IpAddress,
         iface_cx: &mut Context,
-    ) -> Result<EthernetRepr, Option<ArpRepr>> {
+    ) -> Result<EthernetRepr, Option<NeighborRequest>> {
+        let (next_hop_ether, ethertype) = match dst_addr {
+            IpAddress::Ipv4(dst_addr) => (
+                self.resolve_ipv4(&dst_addr, iface_cx)?,
+                EthernetProtocol::Ipv4,
+            ),
+            IpAddress::Ipv6(dst_addr) => (
+                self.resolve_ipv6(&dst_addr, iface_cx)?,
+                EthernetProtocol::Ipv6,
+            ),
+        };
+
+        Ok(EthernetRepr {
+            src_addr: self.ether_addr,
+            dst_addr: next_hop_ether,
+            ethertype,
+        })
+    }
+
+    fn resolve_ipv4(
+        &self,
+        dst_addr: &Ipv4Address,
+        iface_cx: &mut Context,
+    ) -> Result<EthernetAddress, Option<NeighborRequest>> {
         // Resolve the next-hop IP address.
-        let next_hop_ip = match iface_cx.route(&pkt.ip_repr().dst_addr(), iface_cx.now()) {
+        //
+        // The packets have already been routed to this iface, so a destination without any route
+        // via a gateway is reached directly (e.g., by a device route added from user space).
+        let next_hop_ip = match iface_cx.route(&IpAddress::Ipv4(*dst_addr), iface_cx.now()) {
             Some(IpAddress::Ipv4(next_hop_ip)) => next_hop_ip,
-            None => return Err(None),
+            Some(_) => return Err(None),
+            None => *dst_addr,
         };
 
         // Resolve the next-hop Ethernet address.
-        let next_hop_ether = if next_hop_ip.is_broadcast() {
-            EthernetAddress::BROADCAST
+        if next_hop_ip.is_broadcast() {
+            Ok(EthernetAddress::BROADCAST)
         } else if let Some(next_hop_ether) = self.arp_table.lock().get(&next_hop_ip) {
-            *next_hop_ether
+            Ok(*next_hop_ether)
         } else {
             // If the next-hop Ethernet address cannot be resolved, we drop the original packet and
             // send an ARP packet instead. The upper layer should be responsible for detecting the
             // packet loss and retrying later to see if the Ethernet address is ready.
-            return Err(Some(ArpRepr::EthernetIpv4 {
+            Err(Some(NeighborRequest::Arp(ArpRepr::EthernetIpv4 {
                 operation: ArpOperation::Request,
                 source_hardware_addr: self.ether_addr,
                 source_protocol_addr: iface_cx.ipv4_addr().unwrap_or(Ipv4Address::UNSPECIFIED),
                 target_hardware_addr: EthernetAddress::BROADCAST,
                 target_protocol_addr: next_hop_ip,
-            }));
+            })))
+        }
+    }
+
+    fn resolve_ipv6(
+        &self,
+        dst_addr: &Ipv6Address,
+        iface_cx: &mut Context,
+    ) -> Result<EthernetAddress, Option<NeighborRequest>> {
+        if dst_addr.is_multicast() {
+            return Ok(ipv6::multicast_ether_addr(dst_addr));
+        }
+
+        // Resolve the next-hop IP address.
+        //
+        // The packets have already been routed to this iface, so a destination without any route
+        // via a gateway is reached directly (e.g., by a device route added from user space).
+        let next_hop_ip = match iface_cx.route(&IpAddress::Ipv6(*dst_addr), iface_cx.now()) {
+            Some(IpAddress::Ipv6(next_hop_ip)) => next_hop_ip,
+            Some(_) => return Err(None),
+            None => *dst_addr,
         };
 
-        Ok(EthernetRepr {
-            src_addr: self.ether_addr,
-            dst_addr: next_hop_ether,
-            ethertype: EthernetProtocol::Ipv4,
-        })
+        // Resolve the next-hop Ethernet address.
+        if let Some(next_hop_ether) = self.ndisc_table.lock().get(&next_hop_ip) {
+            return Ok(*next_hop_ether);
+        }
+
+        // Similar to ARP, we drop the original packet and send a Neighbor Solicitation message to
+        // the solicited-node multicast address instead. See
+        // <https://datatracker.ietf.org/doc/html/rfc4861#section-7.2.2>.
+        let solicit_repr = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+            target_addr: next_hop_ip,
+            lladdr: Some(RawHardwareAddress::from_bytes(self.ether_addr.as_bytes())),
+        });
+        let solicited_node = ipv6::solicited_node(&next_hop_ip);
+
+        Err(Some(NeighborRequest::Ndisc(
+            EthernetRepr {
+                src_addr: self.ether_addr,
+                dst_addr: ipv6::multicast_ether_addr(&solicited_node),
+                ethertype: EthernetProtocol::Ipv6,
+            },
+            Packet::new_ipv6(
+                Ipv6Repr {
+                    src_addr: self.link_local_addr,
+                    dst_addr: solicited_node,
+                    next_header: IpProtocol::Icmpv6,
+                    payload_len: solicit_repr.buffer_len(),
+                    hop_limit: 255,
+                },
+                IpPayload::Icmpv6(solicit_repr),
+            ),
+        )))
     }
 
-    /// Consumes the token and emits an IP packet.
+    /// Consumes the token and emits an IP packet, which is fragmented if it exceeds the MTU.
     fn emit_ip<T: TxToken>(
+        &self,
         ether_repr: &EthernetRepr,
-        ip_pkt: &Packet,
+        ip_pkt: &OutgoingPacket,
         caps: &DeviceCapabilities,
         tx_token: T,
     ) {
-        tx_token.consume(
-            ether_repr.buffer_len() + ip_pkt.ip_repr().buffer_len(),
-            |buffer| {
-                let mut frame = EthernetFrame::new_unchecked(buffer);
-                ether_repr.emit(&mut frame);
-
-                let ip_repr = ip_pkt.ip_repr();
-                ip_repr.emit(frame.payload_mut(), &caps.checksum);
-                ip_pkt.emit_payload(
-                    &ip_repr,
-                    &mut frame.payload_mut()[ip_repr.header_len()..],
-                    caps,
-                );
-            },
+        self.common.transmit_ip(
+            ip_pkt,
+            caps,
+            ether_repr.buffer_len(),
+            |buffer| ether_repr.emit(&mut EthernetFrame::new_unchecked(buffer)),
+            tx_token,
         );
     }
 
@@ -5,7 +5,7 @@ use alloc::{string::String, sync::Arc};
 use smoltcp::{
     iface::Config,
     phy::{Device, TxToken},
-    wire::{self, Ipv4Cidr, Ipv4Packet},
+    wire::{self, EthernetAddress, Ipv4Cidr},
 };
 
 use crate::{
@@ -14,6 +14,9 @@ use crate::{
     iface::{
         common::{IfaceCommon, InterfaceFlags, InterfaceType},
         iface::internal::IfaceInternal,
+        poll::IpPacket,
+        rx_handler::DivertedFrames,
+        tap::TapDevice,
         time::get_network_timestamp,
         Iface, ScheduleNextPoll,
     },
@@ -27,7 +30,7 @@ pub struct IpIface<D, E: Ext> {
 impl<D: WithDevice, E: Ext> IpIface<D, E> {
     pub fn new(
         driver: D,
-        ip_cidr: Ipv4Cidr,
+        ip_cidr: Option<Ipv4Cidr>,
         name: String,
         sched_poll: E::ScheduleNextPoll,
         type_: InterfaceType,
@@ -40,7 +43,9 @@ impl<D: WithDevice, E: Ext> IpIface<D, E> {
             let mut interface = smoltcp::iface::Interface::new(config, device, now);
             interface.update_ip_addrs(|ip_addrs| {
                 debug_assert!(ip_addrs.is_empty());
-                ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                if let Some(ip_cidr) = ip_cidr {
+                    ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                }
             });
             interface
         });
@@ -59,28 +64,55 @@ impl<D, E: Ext> IfaceInternal<E> for IpIface<D, E> {
 
 impl<D: WithDevice + 'static, E: Ext> Iface<E> for IpIface<D, E> {
     fn poll(&self) {
-        self.driver.with(|device| {
+        let diverted_frames = self.driver.with(|device| {
+            let mut tap_device = TapDevice::<_, E::FrameTap>::new(device, self.common.index());
+
+            let diverted_frames = self
+                .common
+                .rx_handler()
+                .map(|handler| DivertedFrames::take_from(handler, &mut tap_device));
+
             let next_poll = self.common.poll(
-                device,
-                |data, _iface_cx, tx_token| Some((Ipv4Packet::new_checked(data).ok()?, tx_token)),
+                &mut tap_device,
+                |data, _iface_cx, tx_token| Some((IpPacket::new_checked(data)?, tx_token)),
                 |pkt, iface_cx, tx_token| {
-                    let ip_repr = pkt.ip_repr();
-                    tx_token.consume(ip_repr.buffer_len(), |buffer| {
-                        ip_repr.emit(&mut buffer[..], &iface_cx.checksum_caps());
-                        pkt.emit_payload(
-                            &ip_repr,
-                            &mut buffer[ip_repr.header_len()..],
-                            &iface_cx.caps,
-                        );
-                    });
+                    let Some(pkt) = self.common.filter_egress(pkt, iface_cx) else {
+                        return;
+                    };
+
+                    self.common
+                        .transmit_ip(&pkt, &iface_cx.caps, 0, |_| (), tx_token);
                 },
             );
             self.common.sched_poll().schedule_next_poll(next_poll);
+
+            diverted_frames
         });
+
+        if let Some(diverted_frames) = diverted_frames {
+            diverted_frames.deliver(self.common.index());
+        }
     }
 
     fn mtu(&self) -> usize {
         self.driver
             .with(|device| device.capabilities().max_transmission_unit)
     }
+
+    fn ether_addr(&self) -> Option<EthernetAddress> {
+        None
+    }
+
+    fn send_frame(&self, frame: &[u8]) -> bool {
+        self.driver.with(|device| {
+            let mut tap_device = TapDevice::<_, E::FrameTap>::new(device, self.common.index());
+
+            let Some(tx_token) = tap_device.transmit(get_network_timestamp()) else {
+                return false;
+            };
+            tx_token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+
+            true
+        })
+    }
 }
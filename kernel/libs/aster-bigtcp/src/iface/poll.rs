@@ -1,7 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{sync::Arc, vec, vec::Vec};
+use alloc::{collections::vec_deque::VecDeque, sync::Arc, vec, vec::Vec};
 
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
 use smoltcp::{
     iface::{
         packet::{icmp_reply_payload_len, IpPayload, Packet},
@@ -9,13 +11,24 @@ use smoltcp::{
     },
     phy::{ChecksumCapabilities, Device, RxToken, TxToken},
     wire::{
-        Icmpv4DstUnreachable, Icmpv4Repr, IpAddress, IpProtocol, IpRepr, Ipv4Address, Ipv4Packet,
-        Ipv4Repr, TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN,
-        IPV4_MIN_MTU,
+        Icmpv4DstUnreachable, Icmpv4Packet, Icmpv4Repr, Icmpv6DstUnreachable, Icmpv6Packet,
+        Icmpv6Repr, IpAddress, IpEndpoint, IpProtocol, IpRepr, Ipv4Address, Ipv4Packet, Ipv4Repr,
+        Ipv6Address, Ipv6Packet, Ipv6Repr, NdiscNeighborFlags, NdiscRepr, RawHardwareAddress,
+        TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN, IPV4_MIN_MTU,
+        IPV6_HEADER_LEN, IPV6_MIN_MTU,
     },
 };
 
-use super::poll_iface::PollableIfaceMut;
+use super::{
+    filter::{
+        self, FilterVerdict, IcmpUnreachable, OutgoingPacket, PacketFilter, RejectWith,
+        INGRESS_HOOKS, LOCAL_HOOKS,
+    },
+    frag::{self, Reassembler},
+    icmp::{self, ReceivedIcmpError},
+    ipv6::ALL_NODES,
+    poll_iface::PollableIfaceMut,
+};
 use crate::{
     ext::Ext,
     socket::{TcpConnectionBg, TcpProcessResult},
@@ -24,6 +37,7 @@ use crate::{
 
 pub(super) struct PollContext<'a, E: Ext> {
     iface: PollableIfaceMut<'a, E>,
+    iface_index: u32,
     sockets: &'a SocketTable<E>,
     actions: &'a mut Vec<SocketTableAction<E>>,
 }
@@ -40,17 +54,44 @@ pub(super) enum SocketTableAction<E: Ext> {
 impl<'a, E: Ext> PollContext<'a, E> {
     pub(super) fn new(
         iface: PollableIfaceMut<'a, E>,
+        iface_index: u32,
         sockets: &'a SocketTable<E>,
         actions: &'a mut Vec<SocketTableAction<E>>,
     ) -> Self {
         Self {
             iface,
+            iface_index,
             sockets,
             actions,
         }
     }
 }
 
+/// An IPv4 or IPv6 packet.
+pub(super) enum IpPacket<T: AsRef<[u8]>> {
+    Ipv4(Ipv4Packet<T>),
+    Ipv6(Ipv6Packet<T>),
+}
+
+impl<T: AsRef<[u8]>> IpPacket<T> {
+    /// Parses an IP packet whose version is determined by the first four bits.
+    pub(super) fn new_checked(buffer: T) -> Option<Self> {
+        match buffer.as_ref().first()? >> 4 {
+            4 => Ipv4Packet::new_checked(buffer).ok().map(Self::Ipv4),
+            6 => Ipv6Packet::new_checked(buffer).ok().map(Self::Ipv6),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of the IP packet, excluding the padding bytes after the packet.
+    pub(super) fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Ipv4(pkt) => &pkt.as_ref()[..pkt.total_len() as usize],
+            Self::Ipv6(pkt) => &pkt.as_ref()[..IPV6_HEADER_LEN + pkt.payload_len() as usize],
+        }
+    }
+}
+
 // This works around <https://github.com/rust-lang/rust/issues/49601>.
 // See the issue above for details.
 pub(super) trait FnHelper<A, B, C, O>: FnMut(A, B, C) -> O {}
@@ -60,6 +101,7 @@ impl<E: Ext> PollContext<'_, E> {
     pub(super) fn poll_ingress<D, P, Q>(
         &mut self,
         device: &mut D,
+        reassembler: &mut Reassembler,
         process_phy: &mut P,
         dispatch_phy: &mut Q,
     ) where
@@ -68,7 +110,7 @@ impl<E: Ext> PollContext<'_, E> {
             &'pkt [u8],
             &'cx mut Context,
             D::TxToken<'tx>,
-            Option<(Ipv4Packet<&'pkt [u8]>, D::TxToken<'tx>)>,
+            Option<(IpPacket<&'pkt [u8]>, D::TxToken<'tx>)>,
         >,
         Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
     {
@@ -79,7 +121,31 @@ impl<E: Ext> PollContext<'_, E> {
                     return;
                 };
 
-                let Some(reply) = self.parse_and_process_ipv4(pkt) else {
+                let mut reassembled_bytes = Vec::new();
+                let pkt = match pkt {
+                    IpPacket::Ipv4(pkt) if frag::is_fragment(&pkt) => {
+                        let now = self.iface.context().now();
+                        let Some(pkt) = reassembler.process(&pkt, now, &mut reassembled_bytes)
+                        else {
+                            return;
+                        };
+                        IpPacket::Ipv4(pkt)
+                    }
+                    pkt => pkt,
+                };
+
+                let mut filtered_bytes = Vec::new();
+                let (pkt, verdict) = self.filter_ingress(pkt, &mut filtered_bytes);
+
+                let reply = match verdict {
+                    FilterVerdict::Accept => match pkt {
+                        IpPacket::Ipv4(pkt) => self.parse_and_process_ipv4(pkt),
+                        IpPacket::Ipv6(pkt) => self.parse_and_process_ipv6(pkt),
+                    },
+                    FilterVerdict::Drop => return,
+                    FilterVerdict::Reject(reject_with) => self.parse_and_reject(pkt, reject_with),
+                };
+                let Some(reply) = reply else {
                     return;
                 };
 
@@ -88,6 +154,236 @@ impl<E: Ext> PollContext<'_, E> {
         }
     }
 
+    /// Filters a packet that is received from the device.
+    ///
+    /// The filter may rewrite the packet, so the packet is copied to `buffer` before being
+    /// filtered. This method returns the packet that should be processed and the verdict.
+    fn filter_ingress<'pkt>(
+        &self,
+        pkt: IpPacket<&'pkt [u8]>,
+        buffer: &'pkt mut Vec<u8>,
+    ) -> (IpPacket<&'pkt [u8]>, FilterVerdict) {
+        if !E::PacketFilter::is_active() {
+            return (pkt, FilterVerdict::Accept);
+        }
+
+        buffer.extend_from_slice(pkt.as_bytes());
+        let verdict = E::PacketFilter::filter(self.iface_index, INGRESS_HOOKS, buffer);
+
+        // The filter keeps the packet well-formed, so parsing the packet should not fail.
+        match IpPacket::new_checked(&buffer[..]) {
+            Some(filtered_pkt) => (filtered_pkt, verdict),
+            None => (pkt, FilterVerdict::Drop),
+        }
+    }
+
+    /// Generates the error packet that notifies the sender of a rejected packet.
+    fn parse_and_reject<'pkt>(
+        &mut self,
+        pkt: IpPacket<&'pkt [u8]>,
+        reject_with: RejectWith,
+    ) -> Option<Packet<'pkt>> {
+        let checksum_caps = self.iface.context().checksum_caps();
+
+        // Parse the IP header. Ignore the packet if the header is ill-formed.
+        let (ip_repr, ip_payload) = match pkt {
+            IpPacket::Ipv4(pkt) => (
+                IpRepr::Ipv4(Ipv4Repr::parse(&pkt, &checksum_caps).ok()?),
+                pkt.payload(),
+            ),
+            IpPacket::Ipv6(pkt) => (IpRepr::Ipv6(Ipv6Repr::parse(&pkt).ok()?), pkt.payload()),
+        };
+
+        let reason = match reject_with {
+            RejectWith::TcpReset if ip_repr.next_header() == IpProtocol::Tcp => {
+                let tcp_pkt = TcpPacket::new_checked(ip_payload).ok()?;
+                let tcp_repr = TcpRepr::parse(
+                    &tcp_pkt,
+                    &ip_repr.src_addr(),
+                    &ip_repr.dst_addr(),
+                    &checksum_caps,
+                )
+                .ok()?;
+                return Self::generate_tcp_reset(&ip_repr, &tcp_repr)
+                    .map(|(ip_repr, tcp_repr)| Packet::new(ip_repr, IpPayload::Tcp(tcp_repr)));
+            }
+            RejectWith::TcpReset => IcmpUnreachable::Port,
+            RejectWith::IcmpUnreachable(reason) => reason,
+        };
+
+        self.generate_icmp_unreachable(&ip_repr, ip_payload, reason)
+    }
+
+    /// Generates the RST segment that resets the connection of a rejected TCP segment.
+    fn generate_tcp_reset(
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        // "In no case does receipt of a segment containing RST give rise to a RST in response."
+        // See <https://datatracker.ietf.org/doc/html/rfc9293#section-4-1.64>.
+        if tcp_repr.control == TcpControl::Rst {
+            return None;
+        }
+
+        Some(smoltcp::socket::tcp::Socket::rst_reply(ip_repr, tcp_repr))
+    }
+
+    /// Filters a TCP or UDP packet that is delivered between local addresses.
+    ///
+    /// If the packet is accepted, this method returns the IP header and the ports after the
+    /// filter has possibly rewritten them (e.g., to perform NAT).
+    //
+    // TODO: Route the packet to the devices if the filter changes its destination to a non-local
+    // address.
+    fn filter_local(
+        &self,
+        ip_repr: &IpRepr,
+        ports: (u16, u16),
+        pkt: &Packet,
+    ) -> Result<(IpRepr, (u16, u16)), FilterVerdict> {
+        let OutgoingPacket::Filtered(filtered_pkt) = filter::filter_packet::<E::PacketFilter>(
+            self.iface_index,
+            LOCAL_HOOKS,
+            pkt,
+            self.iface.context(),
+        )?
+        else {
+            return Ok((ip_repr.clone(), ports));
+        };
+
+        let (ip_repr, transport) = match (ip_repr, &filtered_pkt) {
+            (IpRepr::Ipv4(repr), IpPacket::Ipv4(filtered_pkt)) => (
+                IpRepr::Ipv4(Ipv4Repr {
+                    src_addr: filtered_pkt.src_addr(),
+                    dst_addr: filtered_pkt.dst_addr(),
+                    ..*repr
+                }),
+                &filtered_pkt.as_ref()[filtered_pkt.header_len() as usize..],
+            ),
+            (IpRepr::Ipv6(repr), IpPacket::Ipv6(filtered_pkt)) => (
+                IpRepr::Ipv6(Ipv6Repr {
+                    src_addr: filtered_pkt.src_addr(),
+                    dst_addr: filtered_pkt.dst_addr(),
+                    ..*repr
+                }),
+                &filtered_pkt.as_ref()[IPV6_HEADER_LEN..],
+            ),
+            // The filter never changes the IP version.
+            _ => return Err(FilterVerdict::Drop),
+        };
+        // Both TCP and UDP headers start with the source port and the destination port.
+        let ports = match transport.get(..4) {
+            Some(ports) => (
+                u16::from_be_bytes([ports[0], ports[1]]),
+                u16::from_be_bytes([ports[2], ports[3]]),
+            ),
+            None => return Err(FilterVerdict::Drop),
+        };
+
+        Ok((ip_repr, ports))
+    }
+
+    /// Filters a TCP segment that is delivered between local addresses.
+    ///
+    /// If the segment is accepted, this method returns the segment that may have been rewritten
+    /// by the filter. If the segment is rejected, this method returns the RST segment that should
+    /// be processed instead. If the segment is dropped, this method returns `Err(None)`.
+    fn filter_local_tcp<'a>(
+        &self,
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr<'a>,
+    ) -> Result<(IpRepr, TcpRepr<'a>), Option<(IpRepr, TcpRepr<'static>)>> {
+        let pkt = Packet::new(ip_repr.clone(), IpPayload::Tcp(*tcp_repr));
+        let ports = (tcp_repr.src_port, tcp_repr.dst_port);
+
+        match self.filter_local(ip_repr, ports, &pkt) {
+            Ok((new_ip_repr, (src_port, dst_port))) => Ok((
+                new_ip_repr,
+                TcpRepr {
+                    src_port,
+                    dst_port,
+                    ..*tcp_repr
+                },
+            )),
+            Err(FilterVerdict::Accept | FilterVerdict::Drop) => Err(None),
+            Err(FilterVerdict::Reject(RejectWith::TcpReset)) => {
+                Err(Self::generate_tcp_reset(ip_repr, tcp_repr))
+            }
+            // TODO: Generate the ICMP message here to abort the connection. Currently, the segment
+            // is silently dropped.
+            Err(FilterVerdict::Reject(RejectWith::IcmpUnreachable(_))) => Err(None),
+        }
+    }
+
+    /// Filters a UDP datagram that is delivered between local addresses.
+    ///
+    /// If the datagram is accepted, this method returns the datagram that may have been
+    /// rewritten by the filter. If the datagram is rejected, this method returns the reason of
+    /// the ICMP error message that should be generated. If the datagram is dropped, this method
+    /// returns `Err(None)`.
+    fn filter_local_udp(
+        &self,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        udp_payload: &[u8],
+    ) -> Result<(IpRepr, UdpRepr), Option<IcmpUnreachable>> {
+        let pkt = Packet::new(ip_repr.clone(), IpPayload::Udp(*udp_repr, udp_payload));
+        let ports = (udp_repr.src_port, udp_repr.dst_port);
+
+        match self.filter_local(ip_repr, ports, &pkt) {
+            Ok((new_ip_repr, (src_port, dst_port))) => {
+                Ok((new_ip_repr, UdpRepr { src_port, dst_port }))
+            }
+            Err(FilterVerdict::Accept | FilterVerdict::Drop) => Err(None),
+            Err(FilterVerdict::Reject(RejectWith::TcpReset)) => Err(Some(IcmpUnreachable::Port)),
+            Err(FilterVerdict::Reject(RejectWith::IcmpUnreachable(reason))) => Err(Some(reason)),
+        }
+    }
+
+    /// Filters and emits a packet that is generated by the stack and delivered between local
+    /// addresses.
+    ///
+    /// If the packet is accepted, this method returns the bytes of the IP packet.
+    fn filter_and_emit_local(&self, pkt: &Packet) -> Option<Vec<u8>> {
+        let outgoing = filter::filter_packet::<E::PacketFilter>(
+            self.iface_index,
+            LOCAL_HOOKS,
+            pkt,
+            self.iface.context(),
+        )
+        .ok()?;
+
+        let mut buffer = vec![0; outgoing.buffer_len()];
+        outgoing.emit(&mut buffer, &self.iface.context().caps);
+        Some(buffer)
+    }
+
+    /// Processes a packet that is generated by the stack and delivered between local addresses.
+    //
+    // Note that this is only used for ICMP messages. TCP segments and UDP datagrams are processed
+    // without being emitted (see `process_local_tcp_until_outgoing` and `dispatch_udp`).
+    fn process_local_packet(&mut self, pkt: &Packet) {
+        if let Some(buffer) = self.filter_and_emit_local(pkt) {
+            self.process_local_bytes(&buffer);
+        }
+    }
+
+    /// Processes the bytes of an IP packet that is delivered between local addresses.
+    ///
+    /// Replies to such a packet are sent to local addresses as well, so they are processed in the
+    /// same way.
+    fn process_local_bytes(&mut self, buffer: &[u8]) {
+        let reply = match IpPacket::new_checked(buffer) {
+            Some(IpPacket::Ipv4(pkt)) => self.parse_and_process_ipv4(pkt),
+            Some(IpPacket::Ipv6(pkt)) => self.parse_and_process_ipv6(pkt),
+            None => None,
+        };
+
+        if let Some(reply) = reply {
+            self.process_local_packet(&reply);
+        }
+    }
+
     fn parse_and_process_ipv4<'pkt>(
         &mut self,
         pkt: Ipv4Packet<&'pkt [u8]>,
@@ -99,7 +395,7 @@ impl<E: Ext> PollContext<'_, E> {
             return self.generate_icmp_unreachable(
                 &IpRepr::Ipv4(repr),
                 pkt.payload(),
-                Icmpv4DstUnreachable::HostUnreachable,
+                IcmpUnreachable::Host,
             );
         }
 
@@ -111,10 +407,265 @@ impl<E: Ext> PollContext<'_, E> {
             IpProtocol::Udp => {
                 self.parse_and_process_udp(&IpRepr::Ipv4(repr), pkt.payload(), &checksum_caps)
             }
+            IpProtocol::Icmp => self.parse_and_process_icmpv4(&repr, pkt.payload(), &checksum_caps),
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_icmpv4<'pkt>(
+        &mut self,
+        ipv4_repr: &Ipv4Repr,
+        ip_payload: &'pkt [u8],
+        checksum_caps: &ChecksumCapabilities,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the ICMPv4 header. Ignore the packet if the header is ill-formed.
+        let icmp_pkt = Icmpv4Packet::new_checked(ip_payload).ok()?;
+
+        if icmp::is_icmpv4_error(&icmp_pkt) {
+            let error = icmp::parse_icmpv4_error(ipv4_repr, &icmp_pkt, checksum_caps)?;
+            self.process_icmp_error(error);
+            return None;
+        }
+
+        let icmp_repr = Icmpv4Repr::parse(&icmp_pkt, checksum_caps).ok()?;
+
+        match icmp_repr {
+            Icmpv4Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            } => {
+                // Like Linux, echo requests sent to broadcast addresses are ignored (see
+                // `icmp_echo_ignore_broadcasts` in
+                // <https://docs.kernel.org/networking/ip-sysctl.html>).
+                if ipv4_repr.dst_addr.is_broadcast() {
+                    return None;
+                }
+
+                let reply_repr = Icmpv4Repr::EchoReply {
+                    ident,
+                    seq_no,
+                    data,
+                };
+
+                Some(Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr: ipv4_repr.dst_addr,
+                        dst_addr: ipv4_repr.src_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: reply_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv4(reply_repr),
+                ))
+            }
+            Icmpv4Repr::EchoReply { ident, .. } => {
+                self.process_echo_reply(&IpRepr::Ipv4(*ipv4_repr), ident, ip_payload);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_ipv6<'pkt>(
+        &mut self,
+        pkt: Ipv6Packet<&'pkt [u8]>,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the IP header. Ignore the packet if the header is ill-formed.
+        //
+        // TODO: Support IPv6 extension headers. Currently, packets with extension headers are
+        // ignored since their `next_header` fields do not match any upper-layer protocols below.
+        let repr = Ipv6Repr::parse(&pkt).ok()?;
+
+        if !repr.dst_addr.is_multicast() && !self.iface.has_ipv6_addr(&repr.dst_addr) {
+            return self.generate_icmp_unreachable(
+                &IpRepr::Ipv6(repr),
+                pkt.payload(),
+                IcmpUnreachable::Host,
+            );
+        }
+
+        let checksum_caps = self.iface.context().checksum_caps();
+        match repr.next_header {
+            IpProtocol::Tcp => {
+                self.parse_and_process_tcp(&IpRepr::Ipv6(repr), pkt.payload(), &checksum_caps)
+            }
+            IpProtocol::Udp => {
+                self.parse_and_process_udp(&IpRepr::Ipv6(repr), pkt.payload(), &checksum_caps)
+            }
+            IpProtocol::Icmpv6 => {
+                self.parse_and_process_icmpv6(&repr, pkt.payload(), &checksum_caps)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_icmpv6<'pkt>(
+        &mut self,
+        ipv6_repr: &Ipv6Repr,
+        ip_payload: &'pkt [u8],
+        checksum_caps: &ChecksumCapabilities,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the ICMPv6 header. Ignore the packet if the header is ill-formed.
+        let icmp_pkt = Icmpv6Packet::new_checked(ip_payload).ok()?;
+
+        if icmp::is_icmpv6_error(&icmp_pkt) {
+            let error = icmp::parse_icmpv6_error(ipv6_repr, &icmp_pkt, checksum_caps)?;
+            self.process_icmp_error(error);
+            return None;
+        }
+
+        let icmp_repr = Icmpv6Repr::parse(
+            &ipv6_repr.src_addr,
+            &ipv6_repr.dst_addr,
+            &icmp_pkt,
+            checksum_caps,
+        )
+        .ok()?;
+
+        match icmp_repr {
+            Icmpv6Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            } => {
+                let src_addr = if ipv6_repr.dst_addr.is_multicast() {
+                    self.iface.ipv6_src_addr(&ipv6_repr.src_addr)?
+                } else {
+                    ipv6_repr.dst_addr
+                };
+                let reply_repr = Icmpv6Repr::EchoReply {
+                    ident,
+                    seq_no,
+                    data,
+                };
+
+                Some(Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr,
+                        dst_addr: ipv6_repr.src_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: reply_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv6(reply_repr),
+                ))
+            }
+            Icmpv6Repr::EchoReply { ident, .. } => {
+                self.process_echo_reply(&IpRepr::Ipv6(*ipv6_repr), ident, ip_payload);
+                None
+            }
+            Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit { target_addr, .. }) => {
+                self.process_neighbor_solicit(ipv6_repr, &target_addr)
+            }
             _ => None,
         }
     }
 
+    /// Delivers an echo reply to the ICMP socket that has sent the echo request.
+    fn process_echo_reply(&self, ip_repr: &IpRepr, ident: u16, message: &[u8]) {
+        for socket in self.sockets.icmp_socket_iter() {
+            if socket.can_process(ident)
+                && socket.process(&ip_repr.src_addr(), &ip_repr.dst_addr(), message)
+            {
+                break;
+            }
+        }
+    }
+
+    /// Reports an ICMP error message to the socket that has sent the undeliverable packet.
+    fn process_icmp_error(&mut self, received: ReceivedIcmpError) {
+        let ReceivedIcmpError {
+            protocol,
+            local_endpoint,
+            error,
+        } = received;
+
+        match protocol {
+            IpProtocol::Tcp => {
+                let connection_key = ConnectionKey::from((local_endpoint, error.remote_endpoint));
+                let Some(connection) = self.sockets.lookup_connection(&connection_key) else {
+                    return;
+                };
+
+                let became_dead = connection.process_icmp_error(&mut self.iface, &error);
+                if *became_dead {
+                    self.actions
+                        .push(SocketTableAction::DelTcpConn(connection_key));
+                }
+            }
+            IpProtocol::Udp => {
+                for socket in self.sockets.udp_socket_iter() {
+                    if socket.can_process(local_endpoint.port)
+                        && socket.process_icmp_error(&local_endpoint.addr, &error)
+                    {
+                        break;
+                    }
+                }
+            }
+            IpProtocol::Icmp | IpProtocol::Icmpv6 => {
+                for socket in self.sockets.icmp_socket_iter() {
+                    if socket.can_process(local_endpoint.port)
+                        && socket.process_icmp_error(&local_endpoint.addr, &error)
+                    {
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Replies to a Neighbor Solicitation message.
+    ///
+    /// Note that the link-layer addresses in Neighbor Discovery messages are learned by the
+    /// Ethernet iface itself, so the only thing to do here is to advertise our own address.
+    ///
+    /// Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-7.2.4>.
+    fn process_neighbor_solicit(
+        &self,
+        ipv6_repr: &Ipv6Repr,
+        target_addr: &Ipv6Address,
+    ) -> Option<Packet<'static>> {
+        // Neighbor Discovery messages must not be forwarded by routers. See
+        // <https://datatracker.ietf.org/doc/html/rfc4861#section-7.1.1>.
+        if ipv6_repr.hop_limit != 255 {
+            return None;
+        }
+
+        let ether_addr = self.iface.ether_addr()?;
+        if !self.iface.has_ipv6_addr(target_addr) {
+            return None;
+        }
+
+        // If the source is unspecified, the solicitation comes from a node performing Duplicate
+        // Address Detection, so the advertisement must be sent to all nodes.
+        let (dst_addr, flags) = if ipv6_repr.src_addr.is_unspecified() {
+            (ALL_NODES, NdiscNeighborFlags::OVERRIDE)
+        } else {
+            (
+                ipv6_repr.src_addr,
+                NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
+            )
+        };
+        let advert_repr = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+            flags,
+            target_addr: *target_addr,
+            lladdr: Some(RawHardwareAddress::from_bytes(ether_addr.as_bytes())),
+        });
+
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: *target_addr,
+                dst_addr,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: advert_repr.buffer_len(),
+                hop_limit: 255,
+            },
+            IpPayload::Icmpv6(advert_repr),
+        ))
+    }
+
     fn parse_and_process_tcp<'pkt>(
         &mut self,
         ip_repr: &IpRepr,
@@ -147,14 +698,30 @@ impl<E: Ext> PollContext<'_, E> {
         ip_repr: &IpRepr,
         tcp_repr: &TcpRepr,
     ) -> Option<(IpRepr, TcpRepr<'static>)> {
-        let (mut ip_repr, mut tcp_repr) = self.process_tcp(ip_repr, tcp_repr)?;
+        let (ip_repr, tcp_repr) = self.process_tcp(ip_repr, tcp_repr)?;
+
+        self.process_local_tcp_until_outgoing(ip_repr, tcp_repr)
+    }
 
+    /// Processes the TCP segment if it is sent to a local address, and then processes the replies
+    /// in the same way until an outgoing segment is generated.
+    fn process_local_tcp_until_outgoing(
+        &mut self,
+        mut ip_repr: IpRepr,
+        mut tcp_repr: TcpRepr<'static>,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
         loop {
             if !self.is_unicast_local(ip_repr.dst_addr()) {
                 return Some((ip_repr, tcp_repr));
             }
 
-            let (new_ip_repr, new_tcp_repr) = self.process_tcp(&ip_repr, &tcp_repr)?;
+            let (new_ip_repr, new_tcp_repr) = match self.filter_local_tcp(&ip_repr, &tcp_repr) {
+                Ok((ip_repr, tcp_repr)) => self.process_tcp(&ip_repr, &tcp_repr)?,
+                Err(rst_reply) => {
+                    let (rst_ip_repr, rst_tcp_repr) = rst_reply?;
+                    self.process_tcp(&rst_ip_repr, &rst_tcp_repr)?
+                }
+            };
             ip_repr = new_ip_repr;
             tcp_repr = new_tcp_repr;
         }
@@ -218,10 +785,14 @@ impl<E: Ext> PollContext<'_, E> {
             }
         }
 
-        // Process packets that request to create new connections second.
-        if tcp_repr.control == TcpControl::Syn && tcp_repr.ack_number.is_none() {
+        // Process packets that request to create new connections second. Besides SYN segments,
+        // ACK segments may complete the handshakes that listeners have started with SYN cookies.
+        let is_syn = tcp_repr.control == TcpControl::Syn && tcp_repr.ack_number.is_none();
+        let may_echo_cookie = tcp_repr.ack_number.is_some()
+            && !matches!(tcp_repr.control, TcpControl::Syn | TcpControl::Rst);
+        if is_syn || may_echo_cookie {
             let listener_key = ListenerKey::new(ip_repr.dst_addr(), tcp_repr.dst_port);
-            if let Some(listener) = self.sockets.lookup_listener(&listener_key) {
+            if let Some(listener) = self.sockets.lookup_listener(&listener_key, &connection_key) {
                 let (processed, new_tcp_conn) =
                     listener.process(&mut self.iface, ip_repr, tcp_repr);
 
@@ -239,13 +810,7 @@ impl<E: Ext> PollContext<'_, E> {
             }
         }
 
-        // "In no case does receipt of a segment containing RST give rise to a RST in response."
-        // See <https://datatracker.ietf.org/doc/html/rfc9293#section-4-1.64>.
-        if tcp_repr.control == TcpControl::Rst {
-            return None;
-        }
-
-        Some(smoltcp::socket::tcp::Socket::rst_reply(ip_repr, tcp_repr))
+        Self::generate_tcp_reset(ip_repr, tcp_repr)
     }
 
     fn parse_and_process_udp<'pkt>(
@@ -265,11 +830,7 @@ impl<E: Ext> PollContext<'_, E> {
         .ok()?;
 
         if !self.process_udp(ip_repr, &udp_repr, udp_pkt.payload()) {
-            return self.generate_icmp_unreachable(
-                ip_repr,
-                ip_payload,
-                Icmpv4DstUnreachable::PortUnreachable,
-            );
+            return self.generate_icmp_unreachable(ip_repr, ip_payload, IcmpUnreachable::Port);
         }
 
         None
@@ -292,49 +853,87 @@ impl<E: Ext> PollContext<'_, E> {
         processed
     }
 
+    /// Generates the ICMP error message that reports an undeliverable packet.
+    ///
+    /// If the packet is sent from a local address, the ICMP error message is processed directly
+    /// and this method returns `None`.
     fn generate_icmp_unreachable<'pkt>(
-        &self,
+        &mut self,
         ip_repr: &IpRepr,
         ip_payload: &'pkt [u8],
-        reason: Icmpv4DstUnreachable,
+        reason: IcmpUnreachable,
     ) -> Option<Packet<'pkt>> {
         if !ip_repr.src_addr().is_unicast() || !ip_repr.dst_addr().is_unicast() {
             return None;
         }
 
-        if self.is_unicast_local(ip_repr.src_addr()) {
-            // In this case, the generating ICMP message will have a local IP address as the
-            // destination. However, since we don't have the ability to handle ICMP messages, we'll
-            // just skip the generation.
-            //
-            // TODO: Generate the ICMP message here once we're able to handle incoming ICMP
-            // messages.
-            return None;
-        }
+        let pkt = match ip_repr {
+            IpRepr::Ipv4(ipv4_repr) => {
+                let reason = match reason {
+                    IcmpUnreachable::NoRoute => Icmpv4DstUnreachable::NetUnreachable,
+                    IcmpUnreachable::Host => Icmpv4DstUnreachable::HostUnreachable,
+                    IcmpUnreachable::Port => Icmpv4DstUnreachable::PortUnreachable,
+                    IcmpUnreachable::AdminProhibited => Icmpv4DstUnreachable::CommProhibited,
+                };
+                let reply_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, IPV4_HEADER_LEN);
+                let icmp_repr = Icmpv4Repr::DstUnreachable {
+                    reason,
+                    header: *ipv4_repr,
+                    data: &ip_payload[..reply_len],
+                };
 
-        let IpRepr::Ipv4(ipv4_repr) = ip_repr;
+                Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr: self
+                            .iface
+                            .context()
+                            .ipv4_addr()
+                            .unwrap_or(Ipv4Address::UNSPECIFIED),
+                        dst_addr: ipv4_repr.src_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv4(icmp_repr),
+                )
+            }
+            IpRepr::Ipv6(ipv6_repr) => {
+                let reason = match reason {
+                    IcmpUnreachable::NoRoute => Icmpv6DstUnreachable::NoRoute,
+                    IcmpUnreachable::Host => Icmpv6DstUnreachable::AddrUnreachable,
+                    IcmpUnreachable::Port => Icmpv6DstUnreachable::PortUnreachable,
+                    IcmpUnreachable::AdminProhibited => Icmpv6DstUnreachable::AdminProhibit,
+                };
+                let reply_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, IPV6_HEADER_LEN);
+                let icmp_repr = Icmpv6Repr::DstUnreachable {
+                    reason,
+                    header: *ipv6_repr,
+                    data: &ip_payload[..reply_len],
+                };
 
-        let reply_len = icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, IPV4_HEADER_LEN);
-        let icmp_repr = Icmpv4Repr::DstUnreachable {
-            reason,
-            header: *ipv4_repr,
-            data: &ip_payload[..reply_len],
+                Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr: self.iface.ipv6_src_addr(&ipv6_repr.src_addr)?,
+                        dst_addr: ipv6_repr.src_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv6(icmp_repr),
+                )
+            }
         };
 
-        Some(Packet::new_ipv4(
-            Ipv4Repr {
-                src_addr: self
-                    .iface
-                    .context()
-                    .ipv4_addr()
-                    .unwrap_or(Ipv4Address::UNSPECIFIED),
-                dst_addr: ipv4_repr.src_addr,
-                next_header: IpProtocol::Icmp,
-                payload_len: icmp_repr.buffer_len(),
-                hop_limit: 64,
-            },
-            IpPayload::Icmpv4(icmp_repr),
-        ))
+        if self.is_unicast_local(ip_repr.src_addr()) {
+            // The ICMP error message is sent to a local address, so it should be delivered to the
+            // local socket that has sent the packet.
+            self.process_local_packet(&pkt);
+            return None;
+        }
+
+        Some(pkt)
     }
 
     /// Returns whether the destination address is the unicast address of a local interface.
@@ -348,17 +947,29 @@ impl<E: Ext> PollContext<'_, E> {
                 .context()
                 .ipv4_addr()
                 .is_some_and(|addr| addr == dst_addr),
+            IpAddress::Ipv6(dst_addr) => self.iface.has_ipv6_addr(&dst_addr),
         }
     }
 }
 
 impl<E: Ext> PollContext<'_, E> {
-    pub(super) fn poll_egress<D, Q>(&mut self, device: &mut D, dispatch_phy: &mut Q)
-    where
+    pub(super) fn poll_egress<D, Q>(
+        &mut self,
+        device: &mut D,
+        pending_frames: &SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+        dispatch_phy: &mut Q,
+    ) where
         D: Device + ?Sized,
         Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
     {
         while let Some(tx_token) = device.transmit(self.iface.context().now()) {
+            // Send the remaining fragments of the previous packets first.
+            let pending_frame = pending_frames.lock().pop_front();
+            if let Some(frame) = pending_frame {
+                tx_token.consume(frame.len(), |buffer| buffer.copy_from_slice(&frame));
+                continue;
+            }
+
             if !self.dispatch_ipv4(tx_token, dispatch_phy) {
                 break;
             }
@@ -376,9 +987,15 @@ impl<E: Ext> PollContext<'_, E> {
             return did_something_tcp;
         };
 
-        let (did_something_udp, _tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
+        let (did_something_udp, tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
+
+        let Some(tx_token) = tx_token else {
+            return did_something_tcp || did_something_udp;
+        };
+
+        let (did_something_icmp, _tx_token) = self.dispatch_icmp(tx_token, dispatch_phy);
 
-        did_something_tcp || did_something_udp
+        did_something_tcp || did_something_udp || did_something_icmp
     }
 
     fn dispatch_tcp<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
@@ -402,7 +1019,8 @@ impl<E: Ext> PollContext<'_, E> {
 
             let (reply, became_dead) =
                 TcpConnectionBg::dispatch(&socket, &mut self.iface, |iface, ip_repr, tcp_repr| {
-                    let mut this = PollContext::new(iface, self.sockets, self.actions);
+                    let mut this =
+                        PollContext::new(iface, self.iface_index, self.sockets, self.actions);
 
                     if !this.is_unicast_local(ip_repr.dst_addr()) {
                         dispatch_phy(
@@ -413,6 +1031,14 @@ impl<E: Ext> PollContext<'_, E> {
                         return None;
                     }
 
+                    // If the segment is rejected, the RST segment will be processed instead.
+                    let (ip_repr, tcp_repr) = match this.filter_local_tcp(ip_repr, tcp_repr) {
+                        Ok(filtered) => filtered,
+                        Err(None) => return None,
+                        Err(Some(rst_reply)) => rst_reply,
+                    };
+                    let (ip_repr, tcp_repr) = (&ip_repr, &tcp_repr);
+
                     if !socket.can_process(tcp_repr.dst_port) {
                         return this.process_tcp(ip_repr, tcp_repr);
                     }
@@ -458,7 +1084,7 @@ impl<E: Ext> PollContext<'_, E> {
                 }
                 (None, Some((ip_repr, tcp_repr))) => {
                     if let Some((new_ip_repr, new_tcp_repr)) =
-                        self.process_tcp_until_outgoing(&ip_repr, &tcp_repr)
+                        self.process_local_tcp_until_outgoing(ip_repr, tcp_repr)
                     {
                         dispatch_phy(
                             &Packet::new(new_ip_repr, IpPayload::Tcp(new_tcp_repr)),
@@ -499,10 +1125,11 @@ impl<E: Ext> PollContext<'_, E> {
 
             let mut deferred = None;
 
-            let (cx, pending) = self.iface.inner_mut();
+            let (cx, ether_addr, ipv6_addrs, pending) = self.iface.inner_mut();
             socket.dispatch(cx, |cx, ip_repr, udp_repr, udp_payload| {
-                let iface = PollableIfaceMut::new(cx, pending);
-                let mut this = PollContext::new(iface, self.sockets, &mut actions);
+                let iface = PollableIfaceMut::new(cx, ether_addr, ipv6_addrs, pending);
+                let mut this =
+                    PollContext::new(iface, self.iface_index, self.sockets, &mut actions);
 
                 if ip_repr.dst_addr().is_broadcast() || !this.is_unicast_local(ip_repr.dst_addr()) {
                     dispatch_phy(
@@ -515,16 +1142,7 @@ impl<E: Ext> PollContext<'_, E> {
                     }
                 }
 
-                if !socket.can_process(udp_repr.dst_port) {
-                    // TODO: Generate the ICMP message here once we're able to handle incoming ICMP
-                    // messages.
-                    let _ = this.process_udp(ip_repr, udp_repr, udp_payload);
-                    return;
-                }
-
-                // We cannot call `process_udp` now because it may cause deadlocks. We will copy
-                // the packet and call `process_udp` after releasing the socket lock.
-                deferred = Some((ip_repr.clone(), {
+                let emit_udp = |ip_repr: &IpRepr, udp_repr: &UdpRepr| {
                     let mut data = vec![0; udp_repr.header_len() + udp_payload.len()];
                     udp_repr.emit(
                         &mut UdpPacket::new_unchecked(&mut data),
@@ -535,15 +1153,45 @@ impl<E: Ext> PollContext<'_, E> {
                         &ChecksumCapabilities::ignored(),
                     );
                     data
-                }));
+                };
+
+                let (ip_repr, udp_repr) =
+                    match this.filter_local_udp(ip_repr, udp_repr, udp_payload) {
+                        Ok(filtered) => filtered,
+                        Err(None) => return,
+                        Err(Some(reason)) => {
+                            // The ICMP error message must be delivered to this socket, which
+                            // cannot be done now because it may cause deadlocks.
+                            deferred =
+                                Some((ip_repr.clone(), emit_udp(ip_repr, udp_repr), Some(reason)));
+                            return;
+                        }
+                    };
+                let (ip_repr, udp_repr) = (&ip_repr, &udp_repr);
+
+                if !socket.can_process(udp_repr.dst_port)
+                    && this.process_udp(ip_repr, udp_repr, udp_payload)
+                {
+                    return;
+                }
+
+                // We cannot call `process_udp` now because it may cause deadlocks. We will copy
+                // the packet and call `process_udp` after releasing the socket lock. If no
+                // sockets can process the packet, the packet is also deferred so that the ICMP
+                // error message can be delivered to this socket.
+                deferred = Some((ip_repr.clone(), emit_udp(ip_repr, udp_repr), None));
             });
 
-            if let Some((ip_repr, ip_payload)) = deferred {
-                if let Some(reply) = self.parse_and_process_udp(
-                    &ip_repr,
-                    &ip_payload,
-                    &ChecksumCapabilities::ignored(),
-                ) {
+            if let Some((ip_repr, ip_payload, reason)) = deferred {
+                let reply = match reason {
+                    None => self.parse_and_process_udp(
+                        &ip_repr,
+                        &ip_payload,
+                        &ChecksumCapabilities::ignored(),
+                    ),
+                    Some(reason) => self.generate_icmp_unreachable(&ip_repr, &ip_payload, reason),
+                };
+                if let Some(reply) = reply {
                     dispatch_phy(&reply, self.iface.context_mut(), tx_token.take().unwrap());
                 }
             }
@@ -560,4 +1208,123 @@ impl<E: Ext> PollContext<'_, E> {
 
         (did_something, tx_token)
     }
+
+    fn dispatch_icmp<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
+    where
+        T: TxToken,
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        let mut tx_token = Some(tx_token);
+        let mut did_something = false;
+
+        let mut actions = Vec::new();
+
+        for socket in self.sockets.icmp_socket_iter() {
+            if !socket.need_dispatch() {
+                continue;
+            }
+
+            did_something = true;
+
+            let mut deferred = None;
+
+            let (cx, ether_addr, ipv6_addrs, pending) = self.iface.inner_mut();
+            socket.dispatch(|local_endpoint, remote_addr, seq_no, data| {
+                let iface = PollableIfaceMut::new(cx, ether_addr, ipv6_addrs, pending);
+                let mut this =
+                    PollContext::new(iface, self.iface_index, self.sockets, &mut actions);
+
+                let Some(pkt) = this.new_echo_request(local_endpoint, remote_addr, seq_no, data)
+                else {
+                    return;
+                };
+
+                if !this.is_unicast_local(*remote_addr) {
+                    dispatch_phy(&pkt, this.iface.context_mut(), tx_token.take().unwrap());
+                    return;
+                }
+
+                // We cannot process the request now because the reply may be delivered to this
+                // socket, which may cause deadlocks. We will copy the request and process it after
+                // releasing the socket lock.
+                deferred = this.filter_and_emit_local(&pkt);
+            });
+
+            if let Some(buffer) = deferred {
+                self.process_local_bytes(&buffer);
+            }
+
+            if tx_token.is_none() {
+                break;
+            }
+        }
+
+        // `actions` should be empty, because the ICMP messages are not sent to TCP connections.
+        debug_assert!(actions.is_empty());
+
+        (did_something, tx_token)
+    }
+
+    /// Builds an echo request sent by an ICMP socket.
+    ///
+    /// The port of `local_endpoint` is the identifier of the echo request.
+    fn new_echo_request<'p>(
+        &self,
+        local_endpoint: &IpEndpoint,
+        remote_addr: &IpAddress,
+        seq_no: u16,
+        data: &'p [u8],
+    ) -> Option<Packet<'p>> {
+        let ident = local_endpoint.port;
+
+        match (local_endpoint.addr, remote_addr) {
+            (IpAddress::Ipv4(local_addr), IpAddress::Ipv4(remote_addr)) => {
+                let src_addr = if local_addr.is_unspecified() {
+                    self.iface.context().ipv4_addr()?
+                } else {
+                    local_addr
+                };
+                let icmp_repr = Icmpv4Repr::EchoRequest {
+                    ident,
+                    seq_no,
+                    data,
+                };
+
+                Some(Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr,
+                        dst_addr: *remote_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv4(icmp_repr),
+                ))
+            }
+            (IpAddress::Ipv6(local_addr), IpAddress::Ipv6(remote_addr)) => {
+                let src_addr = if local_addr.is_unspecified() {
+                    self.iface.ipv6_src_addr(remote_addr)?
+                } else {
+                    local_addr
+                };
+                let icmp_repr = Icmpv6Repr::EchoRequest {
+                    ident,
+                    seq_no,
+                    data,
+                };
+
+                Some(Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr,
+                        dst_addr: *remote_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: 64,
+                    },
+                    IpPayload::Icmpv6(icmp_repr),
+                ))
+            }
+            _ => None,
+        }
+    }
 }
@@ -1,11 +1,17 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::btree_set::BTreeSet, sync::Arc};
+use alloc::{collections::btree_set::BTreeSet, sync::Arc, vec::Vec};
 use core::{
     borrow::Borrow,
     sync::atomic::{AtomicU64, Ordering},
 };
 
+use smoltcp::{
+    iface::Route,
+    wire::{EthernetAddress, HardwareAddress, IpCidr, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
+};
+
+use super::ipv6;
 use crate::{
     ext::Ext,
     socket::{NeedIfacePoll, TcpConnectionBg},
@@ -17,13 +23,32 @@ use crate::{
 /// poll.
 pub(crate) struct PollableIface<E: Ext> {
     interface: smoltcp::iface::Interface,
+    ether_addr: Option<EthernetAddress>,
+    // The IPv6 addresses are also kept here because `smoltcp::iface::Context` does not allow us
+    // to look them up during interface polling.
+    ipv6_addrs: Vec<Ipv6Cidr>,
     pending_conns: PendingConnSet<E>,
 }
 
 impl<E: Ext> PollableIface<E> {
     pub(super) fn new(interface: smoltcp::iface::Interface) -> Self {
+        let ether_addr = match interface.hardware_addr() {
+            HardwareAddress::Ethernet(ether_addr) => Some(ether_addr),
+            _ => None,
+        };
+        let ipv6_addrs = interface
+            .ip_addrs()
+            .iter()
+            .filter_map(|ip_addr| match ip_addr {
+                IpCidr::Ipv6(ipv6_addr) => Some(*ipv6_addr),
+                IpCidr::Ipv4(_) => None,
+            })
+            .collect();
+
         Self {
             interface,
+            ether_addr,
+            ipv6_addrs,
             pending_conns: PendingConnSet::new(),
         }
     }
@@ -31,6 +56,8 @@ impl<E: Ext> PollableIface<E> {
     pub(super) fn as_mut(&mut self) -> PollableIfaceMut<E> {
         PollableIfaceMut {
             context: self.interface.context(),
+            ether_addr: self.ether_addr,
+            ipv6_addrs: &self.ipv6_addrs,
             pending_conns: &mut self.pending_conns,
         }
     }
@@ -39,13 +66,129 @@ impl<E: Ext> PollableIface<E> {
         self.interface.ipv4_addr()
     }
 
+    pub(super) fn ipv4_addrs(&self) -> Vec<Ipv4Cidr> {
+        self.interface
+            .ip_addrs()
+            .iter()
+            .filter_map(|ip_addr| match ip_addr {
+                IpCidr::Ipv4(ipv4_addr) => Some(*ipv4_addr),
+                IpCidr::Ipv6(_) => None,
+            })
+            .collect()
+    }
+
+    pub(super) fn ipv6_addrs(&self) -> &[Ipv6Cidr] {
+        &self.ipv6_addrs
+    }
+
+    /// Adds an IPv4 address.
+    ///
+    /// This method returns `false` if the address cannot be added because there are too many
+    /// addresses. Adding an existing address has no effect.
+    pub(super) fn add_ipv4_addr(&mut self, ipv4_cidr: Ipv4Cidr) -> bool {
+        let ip_cidr = IpCidr::Ipv4(ipv4_cidr);
+        if self.interface.ip_addrs().contains(&ip_cidr) {
+            return true;
+        }
+
+        let mut is_added = false;
+        self.interface.update_ip_addrs(|ip_addrs| {
+            is_added = ip_addrs.push(ip_cidr).is_ok();
+        });
+
+        is_added
+    }
+
+    /// Removes an IPv4 or IPv6 address.
+    ///
+    /// This method returns `false` if the address does not exist.
+    pub(super) fn remove_ip_addr(&mut self, ip_cidr: IpCidr) -> bool {
+        let mut is_removed = false;
+        self.interface.update_ip_addrs(|ip_addrs| {
+            let len = ip_addrs.len();
+            ip_addrs.retain(|addr| *addr != ip_cidr);
+            is_removed = ip_addrs.len() != len;
+        });
+
+        if let IpCidr::Ipv6(ipv6_cidr) = ip_cidr {
+            self.ipv6_addrs.retain(|addr| *addr != ipv6_cidr);
+        }
+
+        is_removed
+    }
+
+    /// Adds an IPv6 address.
+    ///
+    /// This method returns `false` if the address cannot be added because there are too many
+    /// addresses. Adding an existing address has no effect.
+    pub(super) fn add_ipv6_addr(&mut self, ipv6_cidr: Ipv6Cidr) -> bool {
+        if self.ipv6_addrs.contains(&ipv6_cidr) {
+            return true;
+        }
+
+        let mut is_added = false;
+        self.interface.update_ip_addrs(|ip_addrs| {
+            is_added = ip_addrs.push(IpCidr::Ipv6(ipv6_cidr)).is_ok();
+        });
+        if is_added {
+            self.ipv6_addrs.push(ipv6_cidr);
+        }
+
+        is_added
+    }
+
+    /// Sets the default IPv6 gateway.
+    pub(super) fn set_ipv6_gateway(&mut self, gateway: Ipv6Address) {
+        // The route table cannot be full because we never add other routes.
+        let _ = self.interface.routes_mut().add_default_ipv6_route(gateway);
+    }
+
     pub(super) fn prefix_len(&self) -> Option<u8> {
         self.interface
             .ip_addrs()
-            .first()
+            .iter()
+            .find(|ip_addr| matches!(ip_addr, IpCidr::Ipv4(_)))
             .map(|ip_addr| ip_addr.prefix_len())
     }
 
+    pub(super) fn routes(&mut self) -> Vec<Route> {
+        let mut routes = Vec::new();
+        self.interface
+            .routes_mut()
+            .update(|storage| routes.extend(storage.iter().cloned()));
+        routes
+    }
+
+    /// Adds a route, or replaces the route to the same destination.
+    ///
+    /// This method returns `false` if the route cannot be added because there are too many
+    /// routes.
+    pub(super) fn add_route(&mut self, route: Route) -> bool {
+        let mut is_added = false;
+        self.interface.routes_mut().update(|storage| {
+            if let Some(old_route) = storage.iter_mut().find(|old| old.cidr == route.cidr) {
+                *old_route = route;
+                is_added = true;
+            } else {
+                is_added = storage.push(route).is_ok();
+            }
+        });
+        is_added
+    }
+
+    /// Removes the route to the destination.
+    ///
+    /// This method returns `false` if the route does not exist.
+    pub(super) fn remove_route(&mut self, cidr: &IpCidr) -> bool {
+        let mut is_removed = false;
+        self.interface.routes_mut().update(|storage| {
+            let len = storage.len();
+            storage.retain(|route| route.cidr != *cidr);
+            is_removed = storage.len() != len;
+        });
+        is_removed
+    }
+
     /// Returns the next poll time.
     pub(super) fn next_poll_at_ms(&self) -> Option<u64> {
         self.pending_conns.next_poll_at_ms()
@@ -78,6 +221,8 @@ impl<E: Ext> PollableIface<E> {
 /// [`smoltcp`] APIs.
 pub(crate) struct PollableIfaceMut<'a, E: Ext> {
     context: &'a mut smoltcp::iface::Context,
+    ether_addr: Option<EthernetAddress>,
+    ipv6_addrs: &'a [Ipv6Cidr],
     pending_conns: &'a mut PendingConnSet<E>,
 }
 
@@ -87,16 +232,32 @@ pub(crate) struct PollableIfaceMut<'a, E: Ext> {
 impl<'a, E: Ext> PollableIfaceMut<'a, E> {
     pub(crate) fn new(
         context: &'a mut smoltcp::iface::Context,
+        ether_addr: Option<EthernetAddress>,
+        ipv6_addrs: &'a [Ipv6Cidr],
         pending_conns: &'a mut PendingConnSet<E>,
     ) -> Self {
         Self {
             context,
+            ether_addr,
+            ipv6_addrs,
             pending_conns,
         }
     }
 
-    pub(crate) fn inner_mut(&mut self) -> (&mut smoltcp::iface::Context, &mut PendingConnSet<E>) {
-        (self.context, self.pending_conns)
+    pub(crate) fn inner_mut(
+        &mut self,
+    ) -> (
+        &mut smoltcp::iface::Context,
+        Option<EthernetAddress>,
+        &'a [Ipv6Cidr],
+        &mut PendingConnSet<E>,
+    ) {
+        (
+            self.context,
+            self.ether_addr,
+            self.ipv6_addrs,
+            self.pending_conns,
+        )
     }
 }
 
@@ -118,6 +279,23 @@ impl<E: Ext> PollableIfaceMut<'_, E> {
         self.context
     }
 
+    /// Returns the Ethernet address if the iface is an Ethernet iface.
+    pub(super) fn ether_addr(&self) -> Option<EthernetAddress> {
+        self.ether_addr
+    }
+
+    /// Returns whether the IPv6 address belongs to the iface.
+    pub(super) fn has_ipv6_addr(&self, addr: &Ipv6Address) -> bool {
+        self.ipv6_addrs
+            .iter()
+            .any(|ipv6_cidr| ipv6_cidr.address() == *addr)
+    }
+
+    /// Selects an IPv6 source address for sending packets to `dst_addr`.
+    pub(super) fn ipv6_src_addr(&self, dst_addr: &Ipv6Address) -> Option<Ipv6Address> {
+        ipv6::select_src_addr(self.ipv6_addrs, dst_addr)
+    }
+
     /// Updates the next poll time of `socket` to `poll_at`.
     ///
     /// This method (or [`PollableIface::update_next_poll_at_ms`]) should be called after network
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{sync::Arc, vec::Vec};
+
+use smoltcp::phy::{Device, RxToken};
+
+use super::time::get_network_timestamp;
+
+/// A handler that takes over the frames received by an iface.
+///
+/// This is how software devices stacked on top of other ifaces (e.g., bridges) receive frames.
+/// Once a handler is registered, the frames received by the iface are passed to the handler
+/// instead of the network stack of the iface, which means that the iface itself no longer
+/// receives any packets.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/linux/netdevice.h#L430>.
+pub trait RxHandler: Send + Sync {
+    /// Handles a frame received by the iface with the given index.
+    ///
+    /// The frame is handled without holding any locks of the iface, so the handler is free to
+    /// send frames through other ifaces or to poll them.
+    fn handle_frame(&self, iface_index: u32, frame: &[u8]);
+}
+
+/// Frames that are received by the device but taken over by an [`RxHandler`].
+pub(super) struct DivertedFrames {
+    handler: Arc<dyn RxHandler>,
+    frames: Vec<Vec<u8>>,
+}
+
+impl DivertedFrames {
+    /// Takes all the frames that are pending in the device.
+    pub(super) fn take_from<D: Device + ?Sized>(
+        handler: Arc<dyn RxHandler>,
+        device: &mut D,
+    ) -> Self {
+        let mut frames = Vec::new();
+        while let Some((rx_token, _)) = device.receive(get_network_timestamp()) {
+            rx_token.consume(|frame| frames.push(frame.to_vec()));
+        }
+
+        Self { handler, frames }
+    }
+
+    /// Passes the frames to the handler.
+    ///
+    /// This method must be called after the locks of the iface are released.
+    pub(super) fn deliver(self, iface_index: u32) {
+        for frame in self.frames.iter() {
+            self.handler.handle_frame(iface_index, frame);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::marker::PhantomData;
+
+use smoltcp::{
+    phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken},
+    time::Instant,
+};
+
+/// A trait for ifaces to pass the frames that go through the devices to the taps.
+///
+/// This is how packet sockets (i.e., `AF_PACKET` sockets) capture the frames. The frames are
+/// tapped right at the device layer, so the taps see the frames exactly as they are sent or
+/// received by the devices.
+pub trait FrameTap {
+    /// Taps a frame that is sent or received by the iface with the given index.
+    ///
+    /// The format of the frame depends on `medium`. For example, if the medium is
+    /// [`Medium::Ip`], the frame starts with the IP header instead of the Ethernet header.
+    fn tap(iface_index: u32, medium: Medium, direction: FrameDirection, frame: &[u8]);
+}
+
+/// The direction of a tapped frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// The frame is received by the device.
+    Incoming,
+    /// The frame is sent by the device.
+    Outgoing,
+}
+
+/// A device wrapper that passes all the frames that go through the device to the tap.
+pub(super) struct TapDevice<'a, D: ?Sized, T> {
+    device: &'a mut D,
+    iface_index: u32,
+    medium: Medium,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, D: Device + ?Sized, T: FrameTap> TapDevice<'a, D, T> {
+    pub(super) fn new(device: &'a mut D, iface_index: u32) -> Self {
+        let medium = device.capabilities().medium;
+        Self {
+            device,
+            iface_index,
+            medium,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<D: Device + ?Sized, T: FrameTap> Device for TapDevice<'_, D, T> {
+    type RxToken<'b>
+        = TapRxToken<D::RxToken<'b>, T>
+    where
+        Self: 'b;
+    type TxToken<'b>
+        = TapTxToken<D::TxToken<'b>, T>
+    where
+        Self: 'b;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let (rx_token, tx_token) = self.device.receive(timestamp)?;
+        Some((
+            TapRxToken::new(rx_token, self.iface_index, self.medium),
+            TapTxToken::new(tx_token, self.iface_index, self.medium),
+        ))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let tx_token = self.device.transmit(timestamp)?;
+        Some(TapTxToken::new(tx_token, self.iface_index, self.medium))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.device.capabilities()
+    }
+}
+
+pub(super) struct TapRxToken<R, T> {
+    token: R,
+    iface_index: u32,
+    medium: Medium,
+    phantom: PhantomData<T>,
+}
+
+impl<R, T> TapRxToken<R, T> {
+    fn new(token: R, iface_index: u32, medium: Medium) -> Self {
+        Self {
+            token,
+            iface_index,
+            medium,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: RxToken, T: FrameTap> RxToken for TapRxToken<R, T> {
+    fn consume<U, F>(self, f: F) -> U
+    where
+        F: FnOnce(&[u8]) -> U,
+    {
+        self.token.consume(|frame| {
+            T::tap(
+                self.iface_index,
+                self.medium,
+                FrameDirection::Incoming,
+                frame,
+            );
+            f(frame)
+        })
+    }
+}
+
+pub(super) struct TapTxToken<X, T> {
+    token: X,
+    iface_index: u32,
+    medium: Medium,
+    phantom: PhantomData<T>,
+}
+
+impl<X, T> TapTxToken<X, T> {
+    fn new(token: X, iface_index: u32, medium: Medium) -> Self {
+        Self {
+            token,
+            iface_index,
+            medium,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<X: TxToken, T: FrameTap> TxToken for TapTxToken<X, T> {
+    fn consume<U, F>(self, len: usize, f: F) -> U
+    where
+        F: FnOnce(&mut [u8]) -> U,
+    {
+        self.token.consume(len, |frame| {
+            let res = f(frame);
+            T::tap(
+                self.iface_index,
+                self.medium,
+                FrameDirection::Outgoing,
+                frame,
+            );
+            res
+        })
+    }
+}
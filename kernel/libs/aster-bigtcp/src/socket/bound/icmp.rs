@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{collections::vec_deque::VecDeque, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_softirq::BottomHalfDisabled;
+use ostd::sync::SpinLock;
+use smoltcp::wire::{IpAddress, IpEndpoint, IpVersion};
+
+use super::common::{Inner, Socket, SocketBg};
+use crate::{
+    errors::icmp::{RecvError, SendError},
+    ext::Ext,
+    iface::BoundPort,
+    socket::{
+        event::SocketEvents,
+        icmp_error::{IcmpError, MAX_PENDING_ICMP_ERRORS},
+    },
+};
+
+/// A socket that sends ICMP echo requests and receives ICMP echo replies.
+///
+/// This is known as a "ping socket" in Linux. The port number of the socket is used as the
+/// identifier of the echo requests, and only the echo replies with the same identifier are
+/// received.
+pub type IcmpSocket<E> = Socket<IcmpSocketInner, E>;
+
+/// States needed by [`IcmpSocketBg`].
+pub struct IcmpSocketInner {
+    socket: SpinLock<RawIcmpSocket, BottomHalfDisabled>,
+    need_dispatch: AtomicBool,
+}
+
+struct RawIcmpSocket {
+    /// The echo requests to send.
+    send_queue: VecDeque<EchoMessage>,
+    /// The total length of the echo requests to send.
+    send_len: usize,
+    /// The echo replies that have been received.
+    recv_queue: VecDeque<EchoMessage>,
+    /// The total length of the echo replies that have been received.
+    recv_len: usize,
+    /// The ICMP error messages that report undeliverable echo requests.
+    icmp_errors: VecDeque<IcmpError>,
+}
+
+/// An ICMP echo request or an ICMP echo reply.
+struct EchoMessage {
+    /// The remote address.
+    addr: IpAddress,
+    /// The ICMP message, including the ICMP header.
+    message: Vec<u8>,
+}
+
+// ICMP socket buffer sizes:
+pub const ICMP_SEND_BUF_LEN: usize = 65536;
+pub const ICMP_RECV_BUF_LEN: usize = 65536;
+
+/// The length of the ICMP echo header (i.e., the type, the code, the checksum, the identifier,
+/// and the sequence number).
+const ECHO_HEADER_LEN: usize = 8;
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+
+impl<E: Ext> Inner<E> for IcmpSocketInner {
+    type Observer = E::IcmpEventObserver;
+
+    fn on_drop(this: &Arc<SocketBg<Self, E>>) {
+        // An ICMP socket can be removed immediately.
+        this.bound.iface().common().remove_icmp_socket(this);
+    }
+}
+
+pub(crate) type IcmpSocketBg<E> = SocketBg<IcmpSocketInner, E>;
+
+impl<E: Ext> IcmpSocketBg<E> {
+    /// Returns whether the socket is bound to the local address.
+    fn accepts(&self, local_addr: &IpAddress) -> bool {
+        let bound_addr = self.bound.endpoint().addr;
+        bound_addr.version() == local_addr.version()
+            && (bound_addr.is_unspecified() || bound_addr == *local_addr)
+    }
+
+    /// Tries to process an incoming echo reply and returns whether the reply is processed.
+    ///
+    /// The caller should make sure that the identifier of the reply matches the port number of
+    /// the socket (see [`SocketBg::can_process`]).
+    pub(crate) fn process(
+        &self,
+        src_addr: &IpAddress,
+        dst_addr: &IpAddress,
+        message: &[u8],
+    ) -> bool {
+        if !self.accepts(dst_addr) {
+            return false;
+        }
+
+        let mut socket = self.inner.socket.lock();
+
+        // Like UDP sockets, the reply is dropped if the receive buffer is full.
+        if socket.recv_len + message.len() <= ICMP_RECV_BUF_LEN {
+            socket.recv_len += message.len();
+            socket.recv_queue.push_back(EchoMessage {
+                addr: *src_addr,
+                message: message.to_vec(),
+            });
+        }
+
+        drop(socket);
+        self.notify_events(SocketEvents::CAN_RECV);
+
+        true
+    }
+
+    /// Tries to process an ICMP error message and returns whether the message is processed.
+    ///
+    /// The caller should make sure that the identifier of the undeliverable echo request matches
+    /// the port number of the socket (see [`SocketBg::can_process`]).
+    pub(crate) fn process_icmp_error(&self, local_addr: &IpAddress, error: &IcmpError) -> bool {
+        if !self.accepts(local_addr) {
+            return false;
+        }
+
+        let mut socket = self.inner.socket.lock();
+        if socket.icmp_errors.len() < MAX_PENDING_ICMP_ERRORS {
+            socket.icmp_errors.push_back(error.clone());
+        }
+
+        drop(socket);
+        self.notify_events(SocketEvents::ICMP_ERROR);
+
+        true
+    }
+
+    /// Tries to generate an outgoing echo request and dispatches the generated request.
+    ///
+    /// `dispatch` is called with the local endpoint (whose port is the identifier), the remote
+    /// address, the sequence number, and the data of the echo request.
+    pub(crate) fn dispatch<D>(&self, dispatch: D)
+    where
+        D: FnOnce(&IpEndpoint, &IpAddress, u16, &[u8]),
+    {
+        let mut socket = self.inner.socket.lock();
+
+        if let Some(EchoMessage { addr, message }) = socket.send_queue.pop_front() {
+            socket.send_len -= message.len();
+
+            let seq_no = u16::from_be_bytes([message[6], message[7]]);
+            dispatch(
+                &self.bound.endpoint(),
+                &addr,
+                seq_no,
+                &message[ECHO_HEADER_LEN..],
+            );
+        }
+
+        // Dequeuing a request means that we can queue more requests.
+        self.notify_events(SocketEvents::CAN_SEND);
+
+        self.inner
+            .need_dispatch
+            .store(!socket.send_queue.is_empty(), Ordering::Relaxed);
+    }
+
+    /// Returns whether the socket _may_ generate an outgoing packet.
+    ///
+    /// The check is intended to be lock-free and fast, but may have false positives.
+    pub(crate) fn need_dispatch(&self) -> bool {
+        self.inner.need_dispatch.load(Ordering::Relaxed)
+    }
+}
+
+impl<E: Ext> IcmpSocket<E> {
+    /// Binds to a specified endpoint.
+    ///
+    /// The port number of the endpoint is used as the identifier of the echo requests.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn new_bind(bound: BoundPort<E>, observer: E::IcmpEventObserver) -> Self {
+        let raw_socket = RawIcmpSocket {
+            send_queue: VecDeque::new(),
+            send_len: 0,
+            recv_queue: VecDeque::new(),
+            recv_len: 0,
+            icmp_errors: VecDeque::new(),
+        };
+
+        let inner = IcmpSocketInner {
+            socket: SpinLock::new(raw_socket),
+            need_dispatch: AtomicBool::new(false),
+        };
+
+        let socket = Self::new(bound, inner);
+        socket.init_observer(observer);
+        socket
+            .iface()
+            .common()
+            .register_icmp_socket(socket.inner().clone());
+
+        socket
+    }
+
+    /// Sends an ICMP echo request.
+    ///
+    /// The message must include the ICMP header. The identifier in the header is replaced with
+    /// the port number of the socket, and the checksum is calculated when the request is sent.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send(&self, remote_addr: &IpAddress, message: &[u8]) -> Result<(), SendError> {
+        let echo_request = match self.0.bound.endpoint().addr.version() {
+            IpVersion::Ipv4 => ICMPV4_ECHO_REQUEST,
+            IpVersion::Ipv6 => ICMPV6_ECHO_REQUEST,
+        };
+        if remote_addr.version() != self.0.bound.endpoint().addr.version() {
+            return Err(SendError::Unaddressable);
+        }
+
+        // Like Linux, the type must be an echo request and the code must be zero.
+        if message.len() < ECHO_HEADER_LEN || message[0] != echo_request || message[1] != 0 {
+            return Err(SendError::InvalidMessage);
+        }
+        if message.len() > ICMP_SEND_BUF_LEN {
+            return Err(SendError::TooLarge);
+        }
+
+        let mut socket = self.0.inner.socket.lock();
+
+        if socket.send_len + message.len() > ICMP_SEND_BUF_LEN {
+            return Err(SendError::BufferFull);
+        }
+
+        socket.send_len += message.len();
+        socket.send_queue.push_back(EchoMessage {
+            addr: *remote_addr,
+            message: message.to_vec(),
+        });
+
+        self.0.inner.need_dispatch.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Receives an ICMP echo reply.
+    ///
+    /// `f` is called with the ICMP message (including the ICMP header) and the remote address.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv<F, R>(&self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&[u8], IpAddress) -> R,
+    {
+        let mut socket = self.0.inner.socket.lock();
+
+        let Some(EchoMessage { addr, message }) = socket.recv_queue.pop_front() else {
+            return Err(RecvError::Exhausted);
+        };
+        socket.recv_len -= message.len();
+
+        Ok(f(&message, addr))
+    }
+
+    /// Returns whether there are echo replies to receive.
+    pub fn can_recv(&self) -> bool {
+        !self.0.inner.socket.lock().recv_queue.is_empty()
+    }
+
+    /// Returns whether more echo requests can be sent.
+    pub fn can_send(&self) -> bool {
+        self.0.inner.socket.lock().send_len < ICMP_SEND_BUF_LEN
+    }
+
+    /// Takes an ICMP error message that reports an undeliverable echo request.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn take_icmp_error(&self) -> Option<IcmpError> {
+        self.0.inner.socket.lock().icmp_errors.pop_front()
+    }
+
+    /// Returns whether there are ICMP error messages to take.
+    pub fn has_icmp_errors(&self) -> bool {
+        !self.0.inner.socket.lock().icmp_errors.is_empty()
+    }
+}
@@ -3,15 +3,17 @@
 use alloc::{
     boxed::Box,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use core::ops::{Deref, DerefMut};
 
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::{SpinLock, SpinLockGuard};
 use smoltcp::{
+    iface::Context,
     socket::{tcp::State, PollAt},
-    time::Duration,
-    wire::{IpEndpoint, IpRepr, TcpControl, TcpRepr},
+    time::{Duration, Instant},
+    wire::{IpAddress, IpEndpoint, IpRepr, TcpControl, TcpRepr, TcpSeqNumber},
 };
 
 use super::{
@@ -22,10 +24,11 @@ use crate::{
     define_boolean_value,
     errors::tcp::{ConnectError, RecvError, SendError},
     ext::Ext,
-    iface::{BoundPort, PollKey, PollableIfaceMut},
+    iface::{BoundPort, Iface, PollKey, PollableIfaceMut},
     socket::{
         event::SocketEvents,
-        option::{RawTcpOption, RawTcpSetOption},
+        icmp_error::IcmpError,
+        option::{CongestionControl, KeepAlive, RawTcpOption, RawTcpSetOption},
         unbound::{new_tcp_socket, RawTcpSocket},
     },
     socket_table::ConnectionKey,
@@ -48,8 +51,88 @@ pub struct RawTcpSocketExt<E: Ext> {
     is_recv_shut: bool,
     /// Indicates if the socket is closed by a RST packet.
     is_rst_closed: bool,
+    /// The keep alive configuration.
+    keep_alive: Option<KeepAlive>,
+    /// The time when the last packet is received, which is used to send keep alive probes.
+    last_recv_at: Option<Instant>,
+    /// The data held back because the socket is corked, or `None` if the socket is not corked.
+    cork: Option<Cork>,
+    /// The maximum segment size, which decides whether the corked data can fill a segment.
+    max_segment_size: usize,
+    /// The ICMP error message that aborts the connection while it is being established.
+    icmp_error: Option<IcmpError>,
+    /// The offset between the sequence numbers seen by the peer and those of the raw socket.
+    seq_offset: SeqOffset,
+    /// How long accepting the connection is deferred until data arrives.
+    ///
+    /// This is inherited from the listener. See [`RawTcpOption::defer_accept`] for details.
+    defer_accept: Option<Duration>,
+    /// The time after which the connection is ready to be accepted even if no data has arrived,
+    /// or `None` if accepting the connection is not being deferred.
+    accept_deferred_until: Option<Instant>,
 }
 
+/// The offset between the sequence numbers seen by the peer and those of the raw socket.
+///
+/// The offset is nonzero only if the connection is created from a SYN cookie. In this case, the
+/// peer has acknowledged the cookie as our initial sequence number, but the raw socket has chosen
+/// a different one. So the sequence numbers in the outgoing segments and the acknowledgment
+/// numbers in the incoming segments have to be translated.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SeqOffset(i32);
+
+impl SeqOffset {
+    pub(super) const ZERO: Self = Self(0);
+
+    /// Creates the offset from the initial sequence numbers of the raw socket and of the peer's
+    /// view.
+    pub(super) fn new(local_isn: TcpSeqNumber, peer_isn: TcpSeqNumber) -> Self {
+        Self(peer_isn.0.wrapping_sub(local_isn.0))
+    }
+
+    /// Translates a segment sent by the peer to the sequence space of the raw socket.
+    fn incoming<'a>(self, tcp_repr: &TcpRepr<'a>) -> TcpRepr<'a> {
+        if self.0 == 0 {
+            return *tcp_repr;
+        }
+
+        let mut sack_ranges = tcp_repr.sack_ranges;
+        for (left, right) in sack_ranges.iter_mut().flatten() {
+            *left = left.wrapping_sub(self.0 as u32);
+            *right = right.wrapping_sub(self.0 as u32);
+        }
+
+        TcpRepr {
+            ack_number: tcp_repr
+                .ack_number
+                .map(|ack| TcpSeqNumber(ack.0.wrapping_sub(self.0))),
+            sack_ranges,
+            ..*tcp_repr
+        }
+    }
+
+    /// Translates a segment sent by the raw socket to the sequence space of the peer.
+    fn outgoing<'a>(self, tcp_repr: &TcpRepr<'a>) -> TcpRepr<'a> {
+        TcpRepr {
+            seq_number: TcpSeqNumber(tcp_repr.seq_number.0.wrapping_add(self.0)),
+            ..*tcp_repr
+        }
+    }
+}
+
+/// The data held back because the socket is corked.
+struct Cork {
+    /// The data that cannot fill a segment.
+    pending: Vec<u8>,
+    /// The time when the pending data begins to be held back.
+    since: Option<Instant>,
+}
+
+/// The maximum time that the data can be held back because the socket is corked.
+///
+/// Reference: <https://man7.org/linux/man-pages/man7/tcp.7.html>.
+const CORK_TIMEOUT: Duration = Duration::from_millis(200);
+
 impl<E: Ext> Deref for RawTcpSocketExt<E> {
     type Target = RawTcpSocket;
 
@@ -106,6 +189,149 @@ impl<E: Ext> RawTcpSocketExt<E> {
     pub fn is_rst_closed(&self) -> bool {
         self.is_rst_closed
     }
+
+    /// Returns the keep alive configuration.
+    ///
+    /// Unlike [`RawTcpSocket::keep_alive`], which returns the current interval of the raw socket,
+    /// this method returns the full configuration set by [`RawTcpSetOption::set_keep_alive`].
+    pub fn keep_alive_option(&self) -> Option<KeepAlive> {
+        self.keep_alive
+    }
+
+    /// Returns whether the socket is corked.
+    ///
+    /// While the socket is corked, only full-sized segments are sent. The remaining data is held
+    /// back until it can fill a segment, the socket is uncorked, the sending half is closed, or
+    /// the data has been held back for 200 milliseconds.
+    pub fn is_corked(&self) -> bool {
+        self.cork.is_some()
+    }
+
+    /// Returns when the socket should be polled.
+    ///
+    /// This is similar to [`RawTcpSocket::poll_at`], but it also takes into account when the data
+    /// held back by the cork should be sent and when the deferred connection should be accepted.
+    pub fn poll_at(&self, cx: &mut Context) -> PollAt {
+        let mut poll_at = self.socket.poll_at(cx);
+
+        if let Some(since) = self.cork.as_ref().and_then(|cork| cork.since) {
+            poll_at = poll_at.min(PollAt::Time(since + CORK_TIMEOUT));
+        }
+        if let Some(deferred_until) = self.accept_deferred_until {
+            poll_at = poll_at.min(PollAt::Time(deferred_until));
+        }
+
+        poll_at
+    }
+}
+
+impl<E: Ext> RawTcpSocketExt<E> {
+    fn set_keep_alive_option(&mut self, keep_alive: Option<KeepAlive>) {
+        self.keep_alive = keep_alive;
+        KeepAlive::apply(keep_alive, &mut self.socket);
+    }
+
+    /// Updates the keep alive state before an incoming packet is processed.
+    fn on_recv(&mut self, now: Instant) {
+        self.last_recv_at = Some(now);
+
+        // The connection is no longer idle, so the next probe should be sent after the idle time.
+        if let Some(keep_alive) = self.keep_alive {
+            self.socket.set_keep_alive(Some(keep_alive.idle));
+        }
+    }
+
+    /// Updates the time-dependent states before outgoing packets are dispatched.
+    fn on_dispatch(&mut self, now: Instant) {
+        if let (Some(keep_alive), Some(last_recv_at)) = (self.keep_alive, self.last_recv_at) {
+            // The connection has been idle, so the first probe is due and the subsequent probes
+            // should be sent after the probe interval.
+            if now >= last_recv_at + keep_alive.idle {
+                self.socket.set_keep_alive(Some(keep_alive.interval));
+            }
+        }
+
+        if self
+            .cork
+            .as_ref()
+            .and_then(|cork| cork.since)
+            .is_some_and(|since| now >= since + CORK_TIMEOUT)
+        {
+            self.flush_cork();
+        }
+    }
+
+    fn set_corked(&mut self, corked: bool) {
+        if !corked {
+            self.flush_cork();
+            self.cork = None;
+        } else if self.cork.is_none() {
+            self.cork = Some(Cork {
+                pending: Vec::new(),
+                since: None,
+            });
+        }
+    }
+
+    /// Sends the data held back by the cork.
+    fn flush_cork(&mut self) {
+        let Some(cork) = self.cork.as_mut() else {
+            return;
+        };
+
+        if !cork.pending.is_empty() {
+            // The pending data never exceeds the free space of the send buffer. So this can only
+            // fail if the sending half is closed, in which case the data should be dropped.
+            let _ = self.socket.send_slice(&cork.pending);
+            cork.pending.clear();
+        }
+        cork.since = None;
+    }
+
+    /// Sends some data while the socket is corked.
+    ///
+    /// The data is queued to the raw socket only if it can fill a segment. Otherwise, it is held
+    /// back. See [`Self::is_corked`] for details.
+    fn send_corked<F, R>(&mut self, f: F, now: Instant) -> Result<R, SendError>
+    where
+        F: FnOnce(&mut [u8]) -> (usize, R),
+    {
+        if !self.may_send() {
+            return Err(SendError::InvalidState);
+        }
+
+        let mut free_len = self.send_capacity() - self.send_queue();
+        let max_segment_size = self.max_segment_size;
+        let cork = self.cork.as_mut().unwrap();
+
+        // If the pending data occupies all the free space, we have to send it. Otherwise, the
+        // socket can never make progress until the cork times out.
+        if cork.pending.len() >= free_len {
+            self.flush_cork();
+            free_len = self.send_capacity() - self.send_queue();
+        }
+        let cork = self.cork.as_mut().unwrap();
+
+        let old_len = cork.pending.len();
+        cork.pending.resize(free_len, 0);
+        let (written_len, result) = f(&mut cork.pending[old_len..]);
+        cork.pending.truncate(old_len + written_len);
+
+        let full_len = cork.pending.len() / max_segment_size * max_segment_size;
+        if full_len > 0 {
+            let sent_len = self.socket.send_slice(&cork.pending[..full_len])?;
+            debug_assert_eq!(sent_len, full_len);
+            cork.pending.drain(..full_len);
+        }
+
+        if cork.pending.is_empty() {
+            cork.since = None;
+        } else if cork.since.is_none() {
+            cork.since = Some(now);
+        }
+
+        Ok(result)
+    }
 }
 
 define_boolean_value!(
@@ -121,6 +347,7 @@ impl<E: Ext> RawTcpSocketExt<E> {
         old_state: State,
         old_recv_queue: usize,
         is_rst: bool,
+        now: Instant,
     ) -> (SocketEvents, TcpConnBecameDead) {
         let became_dead = if self.state() != State::Established {
             // After the connection is closed by the user, no new data can be read, and such unread
@@ -147,26 +374,25 @@ impl<E: Ext> RawTcpSocketExt<E> {
             if self.state() == State::Closed && is_rst {
                 self.is_rst_closed = true;
             }
-            self.on_new_state(this)
+            self.on_new_state(this, now)
         } else {
             SocketEvents::empty()
         };
 
+        self.check_deferred_accept(this, now);
+
         (events, became_dead)
     }
 
-    fn on_new_state(&mut self, this: &Arc<TcpConnectionBg<E>>) -> SocketEvents {
+    fn on_new_state(&mut self, this: &Arc<TcpConnectionBg<E>>, now: Instant) -> SocketEvents {
         let may_send = self.may_send();
 
         if may_send && !self.has_connected {
             self.has_connected = true;
 
-            if let Some(ref listener) = self.listener {
-                let mut backlog = listener.inner.backlog.lock();
-                if let Some(value) = backlog.connecting.remove(this.connection_key()) {
-                    backlog.connected.push(value);
-                }
-                listener.notify_events(SocketEvents::CAN_RECV);
+            match self.defer_accept {
+                Some(defer_accept) => self.accept_deferred_until = Some(now + defer_accept),
+                None => self.queue_for_accept(this),
             }
         }
 
@@ -181,6 +407,36 @@ impl<E: Ext> RawTcpSocketExt<E> {
         events
     }
 
+    /// Moves the connection from the listener's SYN queue to its accept queue.
+    fn queue_for_accept(&self, this: &Arc<TcpConnectionBg<E>>) {
+        let Some(ref listener) = self.listener else {
+            return;
+        };
+
+        let mut backlog = listener.inner.backlog.lock();
+        if let Some(value) = backlog.connecting.remove(this.connection_key()) {
+            backlog.connected.push(value);
+        }
+        listener.notify_events(SocketEvents::CAN_RECV);
+    }
+
+    /// Checks whether the connection whose acceptance is deferred can be accepted now.
+    ///
+    /// This happens when data arrives, the peer closes its sending half or resets the
+    /// connection, or the deferral times out.
+    fn check_deferred_accept(&mut self, this: &Arc<TcpConnectionBg<E>>, now: Instant) {
+        let Some(deferred_until) = self.accept_deferred_until else {
+            return;
+        };
+
+        if self.recv_queue() == 0 && self.may_recv_new() && now < deferred_until {
+            return;
+        }
+
+        self.accept_deferred_until = None;
+        self.queue_for_accept(this);
+    }
+
     /// Checks whether the TCP connection becomes dead.
     ///
     /// A TCP connection is considered dead when and only when the TCP socket is in the closed
@@ -217,7 +473,10 @@ impl<E: Ext> RawTcpSocketExt<E> {
 impl<E: Ext> TcpConnectionInner<E> {
     pub(super) fn new(
         socket: Box<RawTcpSocket>,
+        option: &RawTcpOption,
+        max_segment_size: usize,
         listener: Option<Arc<TcpListenerBg<E>>>,
+        seq_offset: SeqOffset,
         weak_self: &Weak<TcpConnectionBg<E>>,
     ) -> Self {
         let connection_key = {
@@ -229,13 +488,25 @@ impl<E: Ext> TcpConnectionInner<E> {
 
         let poll_key = PollKey::new(Weak::as_ptr(weak_self).addr());
 
-        let socket_ext = RawTcpSocketExt {
+        // Only connections created by listeners can be deferred.
+        let defer_accept = option.defer_accept.filter(|_| listener.is_some());
+
+        let mut socket_ext = RawTcpSocketExt {
             socket,
             listener,
             has_connected: false,
             is_recv_shut: false,
             is_rst_closed: false,
+            keep_alive: option.keep_alive,
+            last_recv_at: None,
+            cork: None,
+            max_segment_size,
+            icmp_error: None,
+            seq_offset,
+            defer_accept,
+            accept_deferred_until: None,
         };
+        socket_ext.set_corked(option.is_corked);
 
         TcpConnectionInner {
             socket: SpinLock::new(socket_ext),
@@ -294,11 +565,13 @@ impl<E: Ext> TcpConnection<E> {
         option: &RawTcpOption,
         observer: E::TcpEventObserver,
     ) -> Result<Self, (BoundPort<E>, ConnectError)> {
-        let Some(local_endpoint) = bound.endpoint() else {
+        let local_endpoint = bound.endpoint();
+        if local_endpoint.addr.version() != remote_endpoint.addr.version() {
             return Err((bound, ConnectError::Unaddressable));
-        };
+        }
 
         let iface = bound.iface().clone();
+        let max_segment_size = max_segment_size(iface.as_ref(), &remote_endpoint.addr);
         // We have to lock `interface` before locking `sockets`
         // to avoid dead lock due to inconsistent lock orders.
         let mut interface = iface.common().interface();
@@ -315,7 +588,8 @@ impl<E: Ext> TcpConnection<E> {
 
             option.apply(&mut socket);
 
-            if let Err(err) = socket.connect(interface.context_mut(), remote_endpoint, bound.port())
+            if let Err(err) =
+                socket.connect(interface.context_mut(), remote_endpoint, local_endpoint)
             {
                 return Err((bound, err.into()));
             }
@@ -323,8 +597,16 @@ impl<E: Ext> TcpConnection<E> {
             socket
         };
 
-        let connection =
-            Self::new_cyclic(bound, |weak| TcpConnectionInner::new(socket, None, weak));
+        let connection = Self::new_cyclic(bound, |weak| {
+            TcpConnectionInner::new(
+                socket,
+                option,
+                max_segment_size,
+                None,
+                SeqOffset::ZERO,
+                weak,
+            )
+        });
         interface.update_next_poll_at_ms(&connection.0, PollAt::Now);
         connection.init_observer(observer);
 
@@ -357,6 +639,14 @@ impl<E: Ext> TcpConnection<E> {
         }
     }
 
+    /// Returns the ICMP error message that has caused the connection to be refused.
+    ///
+    /// If [`Self::connect_state`] returns [`ConnectState::Refused`] and this method returns
+    /// `None`, the connection is refused by a RST segment.
+    pub fn icmp_error(&self) -> Option<IcmpError> {
+        self.0.inner.lock().icmp_error.clone()
+    }
+
     /// Converts back to the [`BoundPort`].
     ///
     /// This method will succeed if the connection is fully closed and no network events can reach
@@ -383,7 +673,11 @@ impl<E: Ext> TcpConnection<E> {
             socket.is_rst_closed = false;
             return Err(SendError::ConnReset);
         }
-        let result = socket.send(f)?;
+        let result = if socket.is_corked() {
+            socket.send_corked(f, iface.context_mut().now())?
+        } else {
+            socket.send(f)?
+        };
 
         let poll_at = socket.poll_at(iface.context_mut());
         let need_poll = iface.update_next_poll_at_ms(&self.0, poll_at);
@@ -445,6 +739,7 @@ impl<E: Ext> TcpConnection<E> {
             return false;
         }
 
+        socket.flush_cork();
         socket.close();
 
         let poll_at = socket.poll_at(iface.context_mut());
@@ -486,6 +781,7 @@ impl<E: Ext> TcpConnection<E> {
             // If there is unread data, reset the connection immediately.
             socket.abort();
         } else {
+            socket.flush_cork();
             socket.close();
         }
 
@@ -523,11 +819,11 @@ impl<E: Ext> TcpConnection<E> {
 }
 
 impl<E: Ext> RawTcpSetOption for TcpConnection<E> {
-    fn set_keep_alive(&self, interval: Option<Duration>) -> NeedIfacePoll {
+    fn set_keep_alive(&self, keep_alive: Option<KeepAlive>) -> NeedIfacePoll {
         let mut iface = self.iface().common().interface();
         let mut socket = self.0.inner.lock();
 
-        socket.set_keep_alive(interval);
+        socket.set_keep_alive_option(keep_alive);
 
         let poll_at = socket.poll_at(iface.context_mut());
         iface.update_next_poll_at_ms(&self.0, poll_at)
@@ -537,6 +833,51 @@ impl<E: Ext> RawTcpSetOption for TcpConnection<E> {
         let mut socket = self.0.inner.lock();
         socket.set_nagle_enabled(enabled);
     }
+
+    fn set_corked(&self, corked: bool) -> NeedIfacePoll {
+        let mut iface = self.iface().common().interface();
+        let mut socket = self.0.inner.lock();
+
+        socket.set_corked(corked);
+
+        let poll_at = socket.poll_at(iface.context_mut());
+        iface.update_next_poll_at_ms(&self.0, poll_at)
+    }
+
+    fn set_congestion_control(&self, congestion_control: CongestionControl) {
+        let mut socket = self.0.inner.lock();
+        socket.set_congestion_control(congestion_control);
+    }
+
+    fn set_defer_accept(&self, _defer_accept: Option<Duration>) {
+        // `TCP_DEFER_ACCEPT` only affects listening sockets.
+    }
+}
+
+/// Returns the maximum segment size of the segments sent to the remote address via the iface.
+///
+/// The size is only used to decide whether the corked data can fill a segment, so TCP options
+/// and the MSS announced by the peer are ignored.
+pub(super) fn max_segment_size<E: Ext>(iface: &dyn Iface<E>, remote_addr: &IpAddress) -> usize {
+    const ETHERNET_HEADER_LEN: usize = 14;
+    const IPV4_HEADER_LEN: usize = 20;
+    const IPV6_HEADER_LEN: usize = 40;
+    const TCP_HEADER_LEN: usize = 20;
+
+    let link_header_len = if iface.ether_addr().is_some() {
+        ETHERNET_HEADER_LEN
+    } else {
+        0
+    };
+    let ip_header_len = match remote_addr {
+        IpAddress::Ipv4(_) => IPV4_HEADER_LEN,
+        IpAddress::Ipv6(_) => IPV6_HEADER_LEN,
+    };
+
+    iface
+        .mtu()
+        .saturating_sub(link_header_len + ip_header_len + TCP_HEADER_LEN)
+        .max(1)
 }
 
 impl<E: Ext> TcpConnectionBg<E> {
@@ -613,13 +954,20 @@ impl<E: Ext> TcpConnectionBg<E> {
         // to be queued.
         let mut events = SocketEvents::CAN_RECV | SocketEvents::CAN_SEND;
 
-        let result = match socket.process(iface.context_mut(), ip_repr, tcp_repr) {
-            None => TcpProcessResult::Processed,
-            Some((ip_repr, tcp_repr)) => TcpProcessResult::ProcessedWithReply(ip_repr, tcp_repr),
-        };
+        socket.on_recv(iface.context_mut().now());
 
+        let seq_offset = socket.seq_offset;
+        let result =
+            match socket.process(iface.context_mut(), ip_repr, &seq_offset.incoming(tcp_repr)) {
+                None => TcpProcessResult::Processed,
+                Some((ip_repr, tcp_repr)) => {
+                    TcpProcessResult::ProcessedWithReply(ip_repr, seq_offset.outgoing(&tcp_repr))
+                }
+            };
+
+        let now = iface.context_mut().now();
         let (state_events, became_dead) =
-            socket.check_state(self, old_state, old_recv_queue, is_rst);
+            socket.check_state(self, old_state, old_recv_queue, is_rst, now);
         events |= state_events;
 
         self.notify_events(events);
@@ -630,6 +978,46 @@ impl<E: Ext> TcpConnectionBg<E> {
         (result, became_dead)
     }
 
+    /// Processes an ICMP error message that reports an undeliverable segment.
+    ///
+    /// Like Linux, the connection is aborted if it is being established and the error is a hard
+    /// error.
+    //
+    // TODO: Report soft errors and errors on established connections via `SO_ERROR`, and update
+    // the path MTU if the segment is too big.
+    pub(crate) fn process_icmp_error(
+        self: &Arc<Self>,
+        iface: &mut PollableIfaceMut<E>,
+        error: &IcmpError,
+    ) -> TcpConnBecameDead {
+        let mut socket = self.inner.lock();
+
+        if socket.state() != State::SynSent || !error.is_hard() || error.is_too_big() {
+            return TcpConnBecameDead::FALSE;
+        }
+
+        let old_state = socket.state();
+        let old_recv_queue = socket.recv_queue();
+
+        // Abort the connection without sending the RST segment. See the comments in
+        // `Self::process` for how this works.
+        socket.abort();
+        socket
+            .dispatch(iface.context_mut(), |_, _| {
+                Ok::<(), core::convert::Infallible>(())
+            })
+            .unwrap();
+        socket.icmp_error = Some(error.clone());
+
+        let now = iface.context_mut().now();
+        let (events, became_dead) = socket.check_state(self, old_state, old_recv_queue, false, now);
+        self.notify_events(events);
+
+        iface.update_next_poll_at_ms(self, PollAt::Ingress);
+
+        became_dead
+    }
+
     /// Tries to generate an outgoing packet and dispatches the generated packet.
     pub(crate) fn dispatch<D>(
         self: &Arc<Self>,
@@ -646,11 +1034,19 @@ impl<E: Ext> TcpConnectionBg<E> {
         let mut is_rst = false;
         let mut events = SocketEvents::empty();
 
+        let now = iface.context_mut().now();
+        socket.on_dispatch(now);
+
+        let seq_offset = socket.seq_offset;
         let mut reply = None;
-        let (cx, pending) = iface.inner_mut();
+        let (cx, ether_addr, ipv6_addrs, pending) = iface.inner_mut();
         socket
             .dispatch(cx, |cx, (ip_repr, tcp_repr)| {
-                reply = dispatch(PollableIfaceMut::new(cx, pending), &ip_repr, &tcp_repr);
+                reply = dispatch(
+                    PollableIfaceMut::new(cx, ether_addr, ipv6_addrs, pending),
+                    &ip_repr,
+                    &seq_offset.outgoing(&tcp_repr),
+                );
                 Ok::<(), ()>(())
             })
             .unwrap();
@@ -663,11 +1059,14 @@ impl<E: Ext> TcpConnectionBg<E> {
             }
             is_rst |= tcp_repr.control == TcpControl::Rst;
             events |= SocketEvents::CAN_RECV | SocketEvents::CAN_SEND;
-            reply = socket.process(iface.context_mut(), ip_repr, tcp_repr);
+            socket.on_recv(iface.context_mut().now());
+            reply = socket
+                .process(iface.context_mut(), ip_repr, &seq_offset.incoming(tcp_repr))
+                .map(|(ip_repr, tcp_repr)| (ip_repr, seq_offset.outgoing(&tcp_repr)));
         }
 
         let (state_events, became_dead) =
-            socket.check_state(self, old_state, old_recv_queue, is_rst);
+            socket.check_state(self, old_state, old_recv_queue, is_rst, now);
         events |= state_events;
 
         self.notify_events(events);
@@ -5,21 +5,25 @@ use alloc::{boxed::Box, collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::SpinLock;
 use smoltcp::{
-    socket::PollAt,
-    time::Duration,
-    wire::{IpEndpoint, IpRepr, TcpRepr},
+    socket::{tcp::State, PollAt},
+    time::{Duration, Instant},
+    wire::{IpEndpoint, IpRepr, TcpControl, TcpRepr},
 };
 
 use super::{
     common::{Inner, NeedIfacePoll, Socket, SocketBg},
-    tcp_conn::{TcpConnection, TcpConnectionBg, TcpConnectionInner, TcpProcessResult},
+    tcp_conn::{
+        max_segment_size, SeqOffset, TcpConnection, TcpConnectionBg, TcpConnectionInner,
+        TcpProcessResult,
+    },
 };
 use crate::{
     errors::tcp::ListenError,
     ext::Ext,
     iface::{BindPortConfig, BoundPort, PollableIfaceMut},
     socket::{
-        option::{RawTcpOption, RawTcpSetOption},
+        option::{CongestionControl, KeepAlive, RawTcpOption, RawTcpSetOption},
+        syn_cookie::{self, SynCookies},
         unbound::{new_tcp_socket, RawTcpSocket},
     },
     socket_table::{ConnectionKey, ListenerKey},
@@ -29,22 +33,72 @@ pub type TcpListener<E> = Socket<TcpListenerInner<E>, E>;
 
 pub struct TcpBacklog<E: Ext> {
     socket: Box<RawTcpSocket>,
+    /// The options that are inherited by new connections.
+    option: RawTcpOption,
+    max_segment_size: usize,
+    /// The maximum number of connections in each of the SYN queue and the accept queue.
     max_conn: usize,
+    /// The SYN queue, i.e., the connections whose handshakes have not completed.
+    ///
+    /// Connections whose acceptance is deferred (see [`RawTcpOption::defer_accept`]) also stay
+    /// in this queue after their handshakes complete.
     pub(super) connecting: BTreeMap<ConnectionKey, TcpConnection<E>>,
+    /// The accept queue, i.e., the connections that are ready to be accepted.
     pub(super) connected: Vec<TcpConnection<E>>,
+    /// The time when SYN cookies were last sent.
+    last_cookie_at: Option<Instant>,
+}
+
+/// The time after sending SYN cookies during which the cookies echoed back are checked.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/tcp.h>.
+const COOKIE_CHECK_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+impl<E: Ext> TcpBacklog<E> {
+    /// Returns whether the accept queue is full.
+    ///
+    /// Like Linux, the accept queue is full only if it holds _more_ than `max_conn` connections.
+    /// So `listen(fd, 0)` still allows one connection to be accepted.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/sock.h>.
+    fn is_accept_queue_full(&self) -> bool {
+        self.connected.len() > self.max_conn
+    }
+
+    /// Returns whether the SYN queue is full.
+    fn is_syn_queue_full(&self) -> bool {
+        self.connecting.len() >= self.max_conn
+    }
+
+    /// Creates a new raw socket that listens at the same endpoint.
+    fn new_listen_socket(&self) -> Box<RawTcpSocket> {
+        let mut socket = new_tcp_socket();
+        self.option.apply(&mut socket);
+        socket.listen(self.socket.listen_endpoint()).unwrap();
+        socket
+    }
+
+    /// Returns whether SYN cookies have been sent recently, so the ACK segments may echo them.
+    fn may_have_sent_cookies(&self, now: Instant) -> bool {
+        self.last_cookie_at
+            .is_some_and(|sent_at| now < sent_at + COOKIE_CHECK_TIMEOUT)
+    }
 }
 
 /// States needed by [`TcpListenerBg`].
 pub struct TcpListenerInner<E: Ext> {
     pub(super) backlog: SpinLock<TcpBacklog<E>, BottomHalfDisabled>,
     listener_key: ListenerKey,
+    /// Whether the listener can share its [`ListenerKey`] with other listeners (`SO_REUSEPORT`).
+    reuse_port: bool,
 }
 
 impl<E: Ext> TcpListenerInner<E> {
-    fn new(backlog: TcpBacklog<E>, listener_key: ListenerKey) -> Self {
+    fn new(backlog: TcpBacklog<E>, listener_key: ListenerKey, reuse_port: bool) -> Self {
         Self {
             backlog: SpinLock::new(backlog),
             listener_key,
+            reuse_port,
         }
     }
 }
@@ -66,23 +120,27 @@ pub(crate) type TcpListenerBg<E> = SocketBg<TcpListenerInner<E>, E>;
 impl<E: Ext> TcpListener<E> {
     /// Listens at a specified endpoint.
     ///
+    /// If `reuse_port` is true, the listener can listen at the same endpoint as other listeners
+    /// whose `reuse_port` is also true. New connections are distributed across these listeners by
+    /// the hash of the addresses and ports of the connections.
+    ///
     /// Polling the iface is _not_ required after this method succeeds.
     pub fn new_listen(
         bound: BoundPort<E>,
         max_conn: usize,
+        reuse_port: bool,
         option: &RawTcpOption,
         observer: E::TcpEventObserver,
     ) -> Result<Self, (BoundPort<E>, ListenError)> {
-        let Some(local_endpoint) = bound.endpoint() else {
-            return Err((bound, ListenError::Unaddressable));
-        };
+        let local_endpoint = bound.endpoint();
 
         let iface = bound.iface().clone();
+        let max_segment_size = max_segment_size(iface.as_ref(), &local_endpoint.addr);
         let mut sockets = iface.common().sockets();
 
         let listener_key = ListenerKey::new(local_endpoint.addr, local_endpoint.port);
 
-        if sockets.lookup_listener(&listener_key).is_some() {
+        if !sockets.can_insert_listener(&listener_key, reuse_port) {
             return Err((bound, ListenError::AddressInUse));
         }
 
@@ -101,12 +159,15 @@ impl<E: Ext> TcpListener<E> {
         let inner = {
             let backlog = TcpBacklog {
                 socket,
+                option: *option,
+                max_segment_size,
                 max_conn,
                 connecting: BTreeMap::new(),
                 connected: Vec::new(),
+                last_cookie_at: None,
             };
 
-            TcpListenerInner::new(backlog, listener_key)
+            TcpListenerInner::new(backlog, listener_key, reuse_port)
         };
 
         let listener = Self::new(bound, inner);
@@ -138,6 +199,15 @@ impl<E: Ext> TcpListener<E> {
         Some((accepted, remote_endpoint.unwrap()))
     }
 
+    /// Sets the maximum number of connections in each of the SYN queue and the accept queue.
+    ///
+    /// The connections that are already in the queues are kept even if they exceed the new limit.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn set_max_conn(&self, max_conn: usize) {
+        self.0.inner.backlog.lock().max_conn = max_conn;
+    }
+
     /// Returns whether there is a TCP connection to accept.
     ///
     /// It's the caller's responsibility to deal with race conditions when using this method.
@@ -171,32 +241,72 @@ impl<E: Ext> TcpListener<E> {
 }
 
 impl<E: Ext> RawTcpSetOption for TcpListener<E> {
-    fn set_keep_alive(&self, interval: Option<Duration>) -> NeedIfacePoll {
+    fn set_keep_alive(&self, keep_alive: Option<KeepAlive>) -> NeedIfacePoll {
         let mut backlog = self.0.inner.backlog.lock();
-        backlog.socket.set_keep_alive(interval);
+        backlog.option.keep_alive = keep_alive;
+        KeepAlive::apply(keep_alive, &mut backlog.socket);
 
         NeedIfacePoll::FALSE
     }
 
     fn set_nagle_enabled(&self, enabled: bool) {
         let mut backlog = self.0.inner.backlog.lock();
+        backlog.option.is_nagle_enabled = enabled;
         backlog.socket.set_nagle_enabled(enabled);
     }
+
+    fn set_corked(&self, corked: bool) -> NeedIfacePoll {
+        let mut backlog = self.0.inner.backlog.lock();
+        backlog.option.is_corked = corked;
+
+        NeedIfacePoll::FALSE
+    }
+
+    fn set_congestion_control(&self, congestion_control: CongestionControl) {
+        let mut backlog = self.0.inner.backlog.lock();
+        backlog.option.congestion_control = congestion_control;
+        backlog.socket.set_congestion_control(congestion_control);
+    }
+
+    fn set_defer_accept(&self, defer_accept: Option<Duration>) {
+        let mut backlog = self.0.inner.backlog.lock();
+        backlog.option.defer_accept = defer_accept;
+    }
 }
 
 impl<E: Ext> TcpListenerBg<E> {
     pub(crate) const fn listener_key(&self) -> &ListenerKey {
         &self.inner.listener_key
     }
+
+    pub(crate) const fn reuse_port(&self) -> bool {
+        self.inner.reuse_port
+    }
 }
 
 impl<E: Ext> TcpListenerBg<E> {
     /// Tries to process an incoming packet and returns whether the packet is processed.
+    ///
+    /// The packet is either a SYN segment that requests a new connection or an ACK segment that
+    /// may echo a SYN cookie.
     pub(crate) fn process(
         self: &Arc<Self>,
         iface: &mut PollableIfaceMut<E>,
         ip_repr: &IpRepr,
         tcp_repr: &TcpRepr,
+    ) -> (TcpProcessResult, Option<Arc<TcpConnectionBg<E>>>) {
+        if tcp_repr.control == TcpControl::Syn && tcp_repr.ack_number.is_none() {
+            self.process_syn(iface, ip_repr, tcp_repr)
+        } else {
+            self.process_cookie_ack(iface, ip_repr, tcp_repr)
+        }
+    }
+
+    fn process_syn(
+        self: &Arc<Self>,
+        iface: &mut PollableIfaceMut<E>,
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr,
     ) -> (TcpProcessResult, Option<Arc<TcpConnectionBg<E>>>) {
         let mut backlog = self.inner.backlog.lock();
 
@@ -207,13 +317,31 @@ impl<E: Ext> TcpListenerBg<E> {
             return (TcpProcessResult::NotProcessed, None);
         }
 
-        // FIXME: According to the Linux implementation, `max_conn` is the upper bound of
-        // `connected.len()`. We currently limit it to `connected.len() + connecting.len()` for
-        // simplicity.
-        if backlog.connected.len() + backlog.connecting.len() >= backlog.max_conn {
+        // Like Linux, the SYN segment is dropped if the accept queue is full. The peer will
+        // retransmit it later, when the user may have accepted some connections.
+        if backlog.is_accept_queue_full() {
             return (TcpProcessResult::Processed, None);
         }
 
+        let syn_cookies = syn_cookie::syn_cookies();
+        if syn_cookies == SynCookies::Always || backlog.is_syn_queue_full() {
+            if syn_cookies == SynCookies::Disabled {
+                return (TcpProcessResult::Processed, None);
+            }
+
+            let now = iface.context_mut().now();
+            let Some((ip_repr, tcp_repr)) =
+                syn_cookie::syn_ack_reply(ip_repr, tcp_repr, backlog.max_segment_size, now)
+            else {
+                return (TcpProcessResult::Processed, None);
+            };
+            backlog.last_cookie_at = Some(now);
+            return (
+                TcpProcessResult::ProcessedWithReply(ip_repr, tcp_repr),
+                None,
+            );
+        }
+
         let result = match backlog
             .socket
             .process(iface.context_mut(), ip_repr, tcp_repr)
@@ -222,16 +350,82 @@ impl<E: Ext> TcpListenerBg<E> {
             Some((ip_repr, tcp_repr)) => TcpProcessResult::ProcessedWithReply(ip_repr, tcp_repr),
         };
 
-        if backlog.socket.state() == smoltcp::socket::tcp::State::Listen {
+        if backlog.socket.state() == State::Listen {
             return (result, None);
         }
 
-        let new_socket = {
-            let mut socket = new_tcp_socket();
-            RawTcpOption::inherit(&backlog.socket, &mut socket);
-            socket.listen(backlog.socket.listen_endpoint()).unwrap();
-            socket
+        let new_socket = backlog.new_listen_socket();
+        let socket = core::mem::replace(&mut backlog.socket, new_socket);
+        let conn_bg = self.add_connection(&mut backlog, socket, SeqOffset::ZERO);
+
+        iface.update_next_poll_at_ms(&conn_bg, PollAt::Now);
+
+        (result, Some(conn_bg))
+    }
+
+    /// Tries to process an ACK segment that may echo a SYN cookie.
+    ///
+    /// If the cookie is valid, a new connection is created as if the SYN segment had been
+    /// processed normally, and then the ACK segment is processed by the new connection.
+    fn process_cookie_ack(
+        self: &Arc<Self>,
+        iface: &mut PollableIfaceMut<E>,
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr,
+    ) -> (TcpProcessResult, Option<Arc<TcpConnectionBg<E>>>) {
+        let mut backlog = self.inner.backlog.lock();
+
+        let now = iface.context_mut().now();
+        if syn_cookie::syn_cookies() == SynCookies::Disabled || !backlog.may_have_sent_cookies(now)
+        {
+            return (TcpProcessResult::NotProcessed, None);
+        }
+
+        let Some(syn_repr) = syn_cookie::check_ack(ip_repr, tcp_repr, now) else {
+            return (TcpProcessResult::NotProcessed, None);
+        };
+
+        if backlog.is_accept_queue_full() {
+            return (TcpProcessResult::Processed, None);
+        }
+
+        // Replay the handshake with a new socket. The raw socket chooses its own initial
+        // sequence number, which is different from the cookie that the peer has acknowledged. So
+        // the sequence numbers are translated by the connection.
+        let mut socket = backlog.new_listen_socket();
+        let _ = socket.process(iface.context_mut(), ip_repr, &syn_repr);
+
+        let mut local_isn = None;
+        socket
+            .dispatch(iface.context_mut(), |_, (_, syn_ack_repr)| {
+                local_isn = Some(syn_ack_repr.seq_number);
+                Ok::<(), core::convert::Infallible>(())
+            })
+            .unwrap();
+        let Some(local_isn) = local_isn else {
+            return (TcpProcessResult::Processed, None);
         };
+        let cookie = tcp_repr.ack_number.unwrap() - 1;
+
+        let conn_bg = self.add_connection(&mut backlog, socket, SeqOffset::new(local_isn, cookie));
+        drop(backlog);
+
+        // The connection cannot become dead, because the segment is not a RST segment and it
+        // completes the handshake.
+        let (result, _) = conn_bg.process(iface, ip_repr, tcp_repr);
+
+        (result, Some(conn_bg))
+    }
+
+    /// Adds a new connection to the SYN queue.
+    fn add_connection(
+        self: &Arc<Self>,
+        backlog: &mut TcpBacklog<E>,
+        socket: Box<RawTcpSocket>,
+        seq_offset: SeqOffset,
+    ) -> Arc<TcpConnectionBg<E>> {
+        let option = backlog.option;
+        let max_segment_size = backlog.max_segment_size;
 
         let conn = TcpConnection::new_cyclic(
             self.bound
@@ -240,8 +434,11 @@ impl<E: Ext> TcpListenerBg<E> {
                 .unwrap(),
             |weak| {
                 TcpConnectionInner::new(
-                    core::mem::replace(&mut backlog.socket, new_socket),
+                    socket,
+                    &option,
+                    max_segment_size,
                     Some(self.clone()),
+                    seq_offset,
                     weak,
                 )
             },
@@ -251,8 +448,6 @@ impl<E: Ext> TcpListenerBg<E> {
         let old_conn = backlog.connecting.insert(*conn_bg.connection_key(), conn);
         debug_assert!(old_conn.is_none());
 
-        iface.update_next_poll_at_ms(&conn_bg, PollAt::Now);
-
-        (result, Some(conn_bg))
+        conn_bg
     }
 }
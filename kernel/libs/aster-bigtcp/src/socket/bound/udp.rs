@@ -1,14 +1,19 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{boxed::Box, sync::Arc};
-use core::sync::atomic::{AtomicBool, Ordering};
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, sync::Arc};
+use core::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use aster_softirq::BottomHalfDisabled;
 use ostd::sync::SpinLock;
 use smoltcp::{
     iface::Context,
+    phy::PacketMeta,
     socket::udp::UdpMetadata,
-    wire::{IpRepr, UdpRepr},
+    wire::{IpAddress, IpRepr, UdpRepr},
 };
 
 use super::common::{Inner, Socket, SocketBg};
@@ -16,17 +21,123 @@ use crate::{
     errors::udp::SendError,
     ext::Ext,
     iface::BoundPort,
-    socket::{event::SocketEvents, unbound::new_udp_socket, RawUdpSocket},
+    socket::{
+        event::SocketEvents,
+        icmp_error::{IcmpError, MAX_PENDING_ICMP_ERRORS},
+        unbound::new_udp_socket,
+        RawUdpSocket,
+    },
+    time::PacketClock,
 };
 
 pub type UdpSocket<E> = Socket<UdpSocketInner, E>;
 
 /// States needed by [`UdpSocketBg`].
 pub struct UdpSocketInner {
-    socket: SpinLock<Box<RawUdpSocket>, BottomHalfDisabled>,
+    socket: SpinLock<RawUdpSocketExt, BottomHalfDisabled>,
     need_dispatch: AtomicBool,
 }
 
+/// A raw UDP socket with the timestamps of its packets.
+///
+/// Each packet is tagged with an ID in its [`PacketMeta`], so the timestamps can be matched with
+/// the packets when they are received or sent. The IDs increase monotonically (with wrapping).
+struct RawUdpSocketExt {
+    socket: Box<RawUdpSocket>,
+    next_packet_id: u32,
+    /// The IDs and the timestamps of the received packets.
+    recv_timestamps: VecDeque<(u32, Duration)>,
+    /// The IDs and the keys of the packets to send that request transmit timestamps.
+    send_requests: VecDeque<(u32, u32)>,
+    /// The keys and the timestamps of the sent packets that request transmit timestamps.
+    send_timestamps: VecDeque<(u32, Duration)>,
+    /// The ICMP error messages that report undeliverable packets.
+    icmp_errors: VecDeque<IcmpError>,
+}
+
+impl Deref for RawUdpSocketExt {
+    type Target = RawUdpSocket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.socket
+    }
+}
+
+impl DerefMut for RawUdpSocketExt {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.socket
+    }
+}
+
+impl RawUdpSocketExt {
+    fn new(socket: Box<RawUdpSocket>) -> Self {
+        Self {
+            socket,
+            next_packet_id: 0,
+            recv_timestamps: VecDeque::new(),
+            send_requests: VecDeque::new(),
+            send_timestamps: VecDeque::new(),
+            icmp_errors: VecDeque::new(),
+        }
+    }
+
+    fn alloc_packet_meta(&mut self) -> PacketMeta {
+        let mut meta = PacketMeta::default();
+        meta.id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.wrapping_add(1);
+        meta
+    }
+
+    /// Records the timestamp of a packet that is received.
+    ///
+    /// It is hard to tell whether the raw socket drops a packet because its receive buffer is
+    /// full, so a timestamp may be recorded for a dropped packet. Such timestamps are discarded
+    /// when later packets are dequeued, or when there are too many timestamps.
+    fn record_recv_timestamp(&mut self, meta: PacketMeta, timestamp: Duration) {
+        if self.recv_timestamps.len() >= self.packet_recv_capacity() {
+            self.recv_timestamps.pop_front();
+        }
+        self.recv_timestamps.push_back((meta.id, timestamp));
+    }
+
+    /// Records the timestamp of a packet that is sent, if the packet requests it.
+    ///
+    /// Returns whether the timestamp is recorded.
+    fn record_send_timestamp(&mut self, meta: PacketMeta, timestamp: Duration) -> bool {
+        let Some(key) = take_by_packet_id(&mut self.send_requests, meta) else {
+            return false;
+        };
+
+        // Like Linux, the timestamp is dropped if too many timestamps have not been taken.
+        if self.send_timestamps.len() >= self.packet_send_capacity() {
+            return false;
+        }
+
+        self.send_timestamps.push_back((key, timestamp));
+        true
+    }
+}
+
+/// Takes the value associated with the packet from the queue ordered by the packet IDs.
+///
+/// The values associated with the previous packets are discarded, because these packets have
+/// been dropped.
+fn take_by_packet_id<T: Copy>(queue: &mut VecDeque<(u32, T)>, meta: PacketMeta) -> Option<T> {
+    while let Some(&(id, value)) = queue.front() {
+        // The IDs may wrap around, so they are compared by their distance.
+        if (id.wrapping_sub(meta.id) as i32) > 0 {
+            break;
+        }
+
+        queue.pop_front();
+        if id == meta.id {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
 impl<E: Ext> Inner<E> for UdpSocketInner {
     type Observer = E::UdpEventObserver;
 
@@ -55,19 +166,50 @@ impl<E: Ext> UdpSocketBg<E> {
             return false;
         }
 
-        socket.process(
-            cx,
-            smoltcp::phy::PacketMeta::default(),
-            ip_repr,
-            udp_repr,
-            udp_payload,
-        );
+        let old_recv_queue = socket.recv_queue();
+
+        let meta = socket.alloc_packet_meta();
+        socket.process(cx, meta, ip_repr, udp_repr, udp_payload);
+
+        // An empty packet does not change the length of the receive queue, so it is assumed to
+        // be queued unless the receive queue is still empty.
+        let is_queued = if udp_payload.is_empty() {
+            socket.can_recv()
+        } else {
+            socket.recv_queue() != old_recv_queue
+        };
+        if is_queued {
+            socket.record_recv_timestamp(meta, E::PacketClock::now());
+        }
 
         self.notify_events(SocketEvents::CAN_RECV);
 
         true
     }
 
+    /// Tries to process an ICMP error message and returns whether the message is processed.
+    ///
+    /// The caller should make sure that the source port of the undeliverable packet matches the
+    /// port number of the socket (see [`SocketBg::can_process`]).
+    pub(crate) fn process_icmp_error(&self, local_addr: &IpAddress, error: &IcmpError) -> bool {
+        let bound_addr = self.bound.endpoint().addr;
+        if bound_addr.version() != local_addr.version()
+            || (!bound_addr.is_unspecified() && bound_addr != *local_addr)
+        {
+            return false;
+        }
+
+        let mut socket = self.inner.socket.lock();
+        if socket.icmp_errors.len() < MAX_PENDING_ICMP_ERRORS {
+            socket.icmp_errors.push_back(error.clone());
+        }
+
+        drop(socket);
+        self.notify_events(SocketEvents::ICMP_ERROR);
+
+        true
+    }
+
     /// Tries to generate an outgoing packet and dispatches the generated packet.
     pub(crate) fn dispatch<D>(&self, cx: &mut Context, dispatch: D)
     where
@@ -75,15 +217,23 @@ impl<E: Ext> UdpSocketBg<E> {
     {
         let mut socket = self.inner.socket.lock();
 
+        let mut sent_packet = None;
         socket
-            .dispatch(cx, |cx, _meta, (ip_repr, udp_repr, udp_payload)| {
+            .dispatch(cx, |cx, meta, (ip_repr, udp_repr, udp_payload)| {
                 dispatch(cx, &ip_repr, &udp_repr, udp_payload);
+                sent_packet = Some((meta, E::PacketClock::now()));
                 Ok::<(), ()>(())
             })
             .unwrap();
 
         // For UDP, dequeuing a packet means that we can queue more packets.
-        self.notify_events(SocketEvents::CAN_SEND);
+        let mut events = SocketEvents::CAN_SEND;
+        if let Some((meta, timestamp)) = sent_packet {
+            if socket.record_send_timestamp(meta, timestamp) {
+                events |= SocketEvents::TX_TIMESTAMP;
+            }
+        }
+        self.notify_events(events);
 
         self.inner
             .need_dispatch
@@ -106,9 +256,7 @@ impl<E: Ext> UdpSocket<E> {
         bound: BoundPort<E>,
         observer: E::UdpEventObserver,
     ) -> Result<Self, (BoundPort<E>, smoltcp::socket::udp::BindError)> {
-        let Some(local_endpoint) = bound.endpoint() else {
-            return Err((bound, smoltcp::socket::udp::BindError::Unaddressable));
-        };
+        let local_endpoint = bound.endpoint();
 
         let socket = {
             let mut socket = new_udp_socket();
@@ -117,7 +265,7 @@ impl<E: Ext> UdpSocket<E> {
                 return Err((bound, err));
             }
 
-            socket
+            RawUdpSocketExt::new(socket)
         };
 
         let inner = UdpSocketInner {
@@ -147,18 +295,47 @@ impl<E: Ext> UdpSocket<E> {
     where
         F: FnOnce(&mut [u8]) -> R,
     {
+        self.send_with_timestamp(size, meta, None, f)
+    }
+
+    /// Sends some data and requests the transmit timestamp if `timestamp_key` is not `None`.
+    ///
+    /// Once the packet is sent, the timestamp can be taken with [`Self::take_tx_timestamp`],
+    /// along with `timestamp_key`.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send_with_timestamp<F, R>(
+        &self,
+        size: usize,
+        meta: impl Into<UdpMetadata>,
+        timestamp_key: Option<u32>,
+        f: F,
+    ) -> Result<R, SendError>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut meta = meta.into();
+        if meta.endpoint.addr.version() != self.0.bound.endpoint().addr.version() {
+            return Err(SendError::Unaddressable);
+        }
+
         let mut socket = self.0.inner.socket.lock();
 
         if size > socket.packet_send_capacity() {
             return Err(SendError::TooLarge);
         }
 
+        meta.meta = socket.alloc_packet_meta();
         let buffer = match socket.send(size, meta) {
             Ok(data) => data,
             Err(err) => return Err(err.into()),
         };
         let result = f(buffer);
 
+        if let Some(key) = timestamp_key {
+            socket.send_requests.push_back((meta.meta.id, key));
+        }
+
         self.0
             .inner
             .need_dispatch
@@ -169,19 +346,55 @@ impl<E: Ext> UdpSocket<E> {
 
     /// Receives some data.
     ///
+    /// Besides the data and the metadata, `f` is also called with the time at which the packet was
+    /// received, if the time is known.
+    ///
     /// Polling the iface is _not_ required after this method succeeds.
     pub fn recv<F, R>(&self, f: F) -> Result<R, smoltcp::socket::udp::RecvError>
     where
-        F: FnOnce(&[u8], UdpMetadata) -> R,
+        F: FnOnce(&[u8], UdpMetadata, Option<Duration>) -> R,
     {
         let mut socket = self.0.inner.socket.lock();
+        let RawUdpSocketExt {
+            socket,
+            recv_timestamps,
+            ..
+        } = &mut *socket;
 
         let (data, meta) = socket.recv()?;
-        let result = f(data, meta);
+        let timestamp = take_by_packet_id(recv_timestamps, meta.meta);
+        let result = f(data, meta, timestamp);
 
         Ok(result)
     }
 
+    /// Takes the transmit timestamp of a sent packet.
+    ///
+    /// Returns the key specified in [`Self::send_with_timestamp`] and the time at which the packet
+    /// was sent, or `None` if there are no more timestamps.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn take_tx_timestamp(&self) -> Option<(u32, Duration)> {
+        self.0.inner.socket.lock().send_timestamps.pop_front()
+    }
+
+    /// Returns whether there are transmit timestamps to take.
+    pub fn has_tx_timestamps(&self) -> bool {
+        !self.0.inner.socket.lock().send_timestamps.is_empty()
+    }
+
+    /// Takes an ICMP error message that reports an undeliverable packet.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn take_icmp_error(&self) -> Option<IcmpError> {
+        self.0.inner.socket.lock().icmp_errors.pop_front()
+    }
+
+    /// Returns whether there are ICMP error messages to take.
+    pub fn has_icmp_errors(&self) -> bool {
+        !self.0.inner.socket.lock().icmp_errors.is_empty()
+    }
+
     /// Calls `f` with an immutable reference to the associated [`RawUdpSocket`].
     //
     // NOTE: If a mutable reference is required, add a method above that correctly updates the next
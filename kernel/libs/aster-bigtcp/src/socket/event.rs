@@ -19,5 +19,9 @@ bitflags::bitflags! {
         const CLOSED_RECV = 4;
         /// Sending data isn't possible anymore.
         const CLOSED_SEND = 8;
+        /// Transmit timestamps of the sent packets are available.
+        const TX_TIMESTAMP = 16;
+        /// ICMP error messages that report undeliverable packets are received.
+        const ICMP_ERROR = 32;
     }
 }
@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::vec::Vec;
+
+use smoltcp::wire::{IpAddress, IpEndpoint};
+
+/// An ICMP error message that reports that a packet sent by a local socket cannot be delivered.
+///
+/// The message is reported to the socket that has sent the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcmpError {
+    /// Whether the message is an ICMPv6 message.
+    pub is_ipv6: bool,
+    /// The type of the ICMP message.
+    pub type_: u8,
+    /// The code of the ICMP message.
+    pub code: u8,
+    /// The MTU of the next hop if the packet is too big, or zero otherwise.
+    pub mtu: u32,
+    /// The address of the node that generates the ICMP message.
+    pub offender: IpAddress,
+    /// The destination of the packet.
+    ///
+    /// For ICMP sockets, the port is always zero.
+    pub remote_endpoint: IpEndpoint,
+    /// The payload of the packet, excluding the UDP header.
+    ///
+    /// For ICMP sockets, the payload includes the ICMP header. For TCP connections, the payload is
+    /// always empty. The payload may be truncated because ICMP error messages only include the
+    /// beginning of the packet.
+    pub payload: Vec<u8>,
+}
+
+/// The maximum number of ICMP error messages that are queued in a socket.
+///
+/// Linux limits the error queue by the size of the receive buffer. Similarly, new messages are
+/// dropped if the limit is reached.
+pub(crate) const MAX_PENDING_ICMP_ERRORS: usize = 64;
+
+// ICMPv4 types and codes.
+// Reference: <https://www.iana.org/assignments/icmp-parameters/icmp-parameters.xhtml>.
+pub(crate) const ICMPV4_DEST_UNREACH: u8 = 3;
+pub(crate) const ICMPV4_TIME_EXCEEDED: u8 = 11;
+pub(crate) const ICMPV4_PARAMETER_PROBLEM: u8 = 12;
+const ICMPV4_FRAG_NEEDED: u8 = 4;
+
+// ICMPv6 types and codes.
+// Reference: <https://www.iana.org/assignments/icmpv6-parameters/icmpv6-parameters.xhtml>.
+pub(crate) const ICMPV6_DEST_UNREACH: u8 = 1;
+pub(crate) const ICMPV6_PKT_TOOBIG: u8 = 2;
+pub(crate) const ICMPV6_TIME_EXCEEDED: u8 = 3;
+pub(crate) const ICMPV6_PARAMETER_PROBLEM: u8 = 4;
+
+impl IcmpError {
+    /// Returns whether the packet is dropped because it is too big.
+    ///
+    /// This is the case for the "Fragmentation Needed" ICMPv4 messages and the "Packet Too Big"
+    /// ICMPv6 messages. [`Self::mtu`] is valid only if this method returns true.
+    pub fn is_too_big(&self) -> bool {
+        if self.is_ipv6 {
+            self.type_ == ICMPV6_PKT_TOOBIG
+        } else {
+            self.type_ == ICMPV4_DEST_UNREACH && self.code == ICMPV4_FRAG_NEEDED
+        }
+    }
+
+    /// Returns whether the error is a hard error.
+    ///
+    /// A hard error indicates that the destination can never be reached, so a connection should
+    /// be aborted, whereas a soft error may be transient (e.g., due to routing problems).
+    ///
+    /// Like Linux with the default path MTU discovery settings, the error is considered hard if
+    /// the packet is too big.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/icmp.c#L113> and
+    /// <https://elixir.bootlin.com/linux/v6.13/source/net/ipv6/icmp.c#L1112>.
+    pub fn is_hard(&self) -> bool {
+        if self.is_too_big() {
+            return true;
+        }
+
+        if self.is_ipv6 {
+            match self.type_ {
+                // Administratively prohibited, port unreachable, source address failed
+                // ingress/egress policy, and reject route to destination.
+                ICMPV6_DEST_UNREACH => matches!(self.code, 1 | 4 | 5 | 6),
+                ICMPV6_PARAMETER_PROBLEM => true,
+                _ => false,
+            }
+        } else {
+            match self.type_ {
+                // All codes except network/host unreachable, source route failed, and
+                // network/host unreachable for TOS.
+                ICMPV4_DEST_UNREACH => !matches!(self.code, 0 | 1 | 5 | 11 | 12),
+                ICMPV4_PARAMETER_PROBLEM => true,
+                _ => false,
+            }
+        }
+    }
+}
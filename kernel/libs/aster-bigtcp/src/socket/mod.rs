@@ -2,15 +2,26 @@
 
 mod bound;
 mod event;
+mod icmp_error;
 mod option;
+mod syn_cookie;
 mod unbound;
 
 pub use bound::{
-    ConnectState, NeedIfacePoll, RawTcpSocketExt, TcpConnection, TcpListener, UdpSocket,
+    ConnectState, IcmpSocket, NeedIfacePoll, RawTcpSocketExt, TcpConnection, TcpListener,
+    UdpSocket, ICMP_RECV_BUF_LEN, ICMP_SEND_BUF_LEN,
+};
+pub(crate) use bound::{
+    IcmpSocketBg, TcpConnectionBg, TcpListenerBg, TcpProcessResult, UdpSocketBg,
 };
-pub(crate) use bound::{TcpConnectionBg, TcpListenerBg, TcpProcessResult, UdpSocketBg};
 pub use event::{SocketEventObserver, SocketEvents};
-pub use option::{RawTcpOption, RawTcpSetOption};
+pub use icmp_error::IcmpError;
+pub(crate) use icmp_error::{
+    ICMPV4_DEST_UNREACH, ICMPV4_PARAMETER_PROBLEM, ICMPV4_TIME_EXCEEDED, ICMPV6_DEST_UNREACH,
+    ICMPV6_PARAMETER_PROBLEM, ICMPV6_PKT_TOOBIG, ICMPV6_TIME_EXCEEDED,
+};
+pub use option::{CongestionControl, KeepAlive, RawTcpOption, RawTcpSetOption};
+pub use syn_cookie::{set_syn_cookie_random_source, set_syn_cookies, syn_cookies, SynCookies};
 pub use unbound::{
     RawUdpSocket, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
 };
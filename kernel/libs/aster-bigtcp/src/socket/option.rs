@@ -1,38 +1,106 @@
 // SPDX-License-Identifier: MPL-2.0
 
+// TODO: The congestion control algorithms are built into smoltcp, so only Reno and CUBIC can be
+// selected. A pluggable congestion control interface, SACK-based loss recovery, and tunable RTT
+// estimation and RTO backoff all require changes to the TCP state machine in smoltcp.
+pub use smoltcp::socket::tcp::CongestionControl;
 use smoltcp::time::Duration;
 
 use super::{unbound::RawTcpSocket, NeedIfacePoll};
 
 /// A trait defines setting socket options on a raw socket.
 pub trait RawTcpSetOption {
-    /// Sets the keep alive interval.
+    /// Sets the keep alive configuration.
     ///
     /// Polling the iface _may_ be required after this method succeeds.
-    fn set_keep_alive(&self, interval: Option<Duration>) -> NeedIfacePoll;
+    fn set_keep_alive(&self, keep_alive: Option<KeepAlive>) -> NeedIfacePoll;
 
     /// Enables or disables Nagle’s Algorithm.
     ///
     /// Polling the iface is _not_ required after this method succeeds.
     fn set_nagle_enabled(&self, enabled: bool);
+
+    /// Corks or uncorks the socket.
+    ///
+    /// While the socket is corked, partial segments are not sent. See
+    /// [`RawTcpSocketExt::is_corked`] for details.
+    ///
+    /// Polling the iface _may_ be required after this method succeeds.
+    ///
+    /// [`RawTcpSocketExt::is_corked`]: super::RawTcpSocketExt::is_corked
+    fn set_corked(&self, corked: bool) -> NeedIfacePoll;
+
+    /// Sets the congestion control algorithm.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    fn set_congestion_control(&self, congestion_control: CongestionControl);
+
+    /// Sets how long accepting new connections is deferred until data arrives.
+    ///
+    /// This only affects listening sockets. See [`RawTcpOption::defer_accept`] for details.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    fn set_defer_accept(&self, defer_accept: Option<Duration>);
 }
 
 /// Socket options on a raw socket.
+#[derive(Debug, Clone, Copy)]
 pub struct RawTcpOption {
-    /// The keep alive interval.
-    pub keep_alive: Option<Duration>,
+    /// The keep alive configuration.
+    pub keep_alive: Option<KeepAlive>,
     /// Whether Nagle's algorithm is enabled.
     pub is_nagle_enabled: bool,
+    /// Whether the socket is corked.
+    pub is_corked: bool,
+    /// The congestion control algorithm.
+    pub congestion_control: CongestionControl,
+    /// How long accepting new connections is deferred until data arrives (`TCP_DEFER_ACCEPT`).
+    ///
+    /// If it is `Some`, a new connection is not ready to be accepted when the handshake
+    /// completes. Instead, it becomes ready when the first data or FIN arrives, or when the
+    /// duration has elapsed since the handshake completed.
+    pub defer_accept: Option<Duration>,
 }
 
 impl RawTcpOption {
     pub(super) fn apply(&self, socket: &mut RawTcpSocket) {
-        socket.set_keep_alive(self.keep_alive);
+        KeepAlive::apply(self.keep_alive, socket);
         socket.set_nagle_enabled(self.is_nagle_enabled);
+        socket.set_congestion_control(self.congestion_control);
+    }
+}
+
+/// The keep alive configuration.
+///
+/// The first probe is sent after the connection has been idle for `idle`. If the peer does not
+/// respond, subsequent probes are sent every `interval`. The connection is dropped after `probes`
+/// probes have not been responded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// The time that the connection needs to be idle before the first probe is sent.
+    pub idle: Duration,
+    /// The time between two probes.
+    pub interval: Duration,
+    /// The number of probes that can be unresponded before the connection is dropped.
+    pub probes: u32,
+}
+
+impl KeepAlive {
+    /// Applies the configuration to the raw socket.
+    ///
+    /// The raw socket only knows a single keep alive interval, which is set to the idle time here.
+    /// It will be switched to the probe interval once the connection becomes idle (see
+    /// [`RawTcpSocketExt`]). The raw socket also aborts the connection if nothing is received
+    /// within its timeout, which implements the probe limit.
+    ///
+    /// [`RawTcpSocketExt`]: super::RawTcpSocketExt
+    pub(super) fn apply(keep_alive: Option<Self>, socket: &mut RawTcpSocket) {
+        socket.set_keep_alive(keep_alive.map(|keep_alive| keep_alive.idle));
+        socket.set_timeout(keep_alive.map(|keep_alive| keep_alive.timeout()));
     }
 
-    pub(super) fn inherit(from: &RawTcpSocket, to: &mut RawTcpSocket) {
-        to.set_keep_alive(from.keep_alive());
-        to.set_nagle_enabled(from.nagle_enabled());
+    /// Returns the time after which the connection is dropped if nothing is received.
+    fn timeout(&self) -> Duration {
+        self.idle + self.interval * self.probes
     }
 }
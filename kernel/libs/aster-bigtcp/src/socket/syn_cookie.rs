@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SYN cookies, which allow listeners to complete handshakes without keeping any states.
+//!
+//! When the SYN queue of a listener overflows, the listener replies to a SYN segment without
+//! remembering it. Instead, the information needed to create the connection is encoded in the
+//! initial sequence number of the SYN-ACK segment (i.e., the cookie). If the peer completes the
+//! handshake, the cookie is echoed back in the ACK segment, from which the connection is created.
+//!
+//! Like Linux, the cookie only encodes an approximation of the MSS announced by the peer. Other
+//! TCP options (e.g., window scaling and SACK) are not available in connections created from
+//! cookies.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/syncookies.c>.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use int_to_c_enum::TryFromInt;
+use smoltcp::{
+    time::Instant,
+    wire::{IpAddress, IpProtocol, IpRepr, TcpControl, TcpRepr, TcpSeqNumber},
+};
+use spin::once::Once;
+
+use super::TCP_RECV_BUF_LEN;
+
+/// When SYN cookies are sent (`/proc/sys/net/ipv4/tcp_syncookies`).
+///
+/// Reference: <https://docs.kernel.org/networking/ip-sysctl.html#tcp-variables>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub enum SynCookies {
+    /// SYN cookies are never sent.
+    Disabled = 0,
+    /// SYN cookies are sent when the SYN queue overflows.
+    OnOverflow = 1,
+    /// SYN cookies are always sent.
+    Always = 2,
+}
+
+static SYN_COOKIES: AtomicU8 = AtomicU8::new(SynCookies::OnOverflow as u8);
+
+/// Returns when SYN cookies are sent.
+pub fn syn_cookies() -> SynCookies {
+    SynCookies::try_from(SYN_COOKIES.load(Ordering::Relaxed)).unwrap()
+}
+
+/// Sets when SYN cookies are sent.
+pub fn set_syn_cookies(syn_cookies: SynCookies) {
+    SYN_COOKIES.store(syn_cookies as u8, Ordering::Relaxed);
+}
+
+/// The MSS values that can be encoded in cookies.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/syncookies.c>.
+const MSS_TABLE: [u16; 4] = [536, 1300, 1440, 1460];
+
+/// The number of bits in a cookie that are used to encode the MSS and verify the cookie.
+const COOKIE_BITS: u32 = 24;
+const COOKIE_MASK: u32 = (1 << COOKIE_BITS) - 1;
+
+/// The number of counter ticks after which cookies expire.
+const MAX_COOKIE_AGE: u32 = 2;
+
+/// The interval between two counter ticks, in milliseconds.
+const COUNTER_INTERVAL_MS: i64 = 60 * 1000;
+
+/// The function that fills a buffer with random bytes, which is provided by the kernel.
+static RANDOM_SOURCE: Once<fn(&mut [u8])> = Once::new();
+
+/// The SipHash keys used to generate cookies.
+///
+/// Like Linux's `net_get_random_once`, the keys are generated when cookies are used for the
+/// first time, so that the random number generator is more likely to be ready.
+static COOKIE_SECRETS: Once<[[u64; 2]; 2]> = Once::new();
+
+/// Sets the function that generates the random secrets of cookies.
+///
+/// Cookies will not be sent until this function is called.
+pub fn set_syn_cookie_random_source(fill_random: fn(&mut [u8])) {
+    RANDOM_SOURCE.call_once(|| fill_random);
+}
+
+fn cookie_secrets() -> Option<&'static [[u64; 2]; 2]> {
+    COOKIE_SECRETS
+        .try_call_once(|| {
+            let fill_random = RANDOM_SOURCE.get().ok_or(())?;
+
+            let mut bytes = [0u8; 32];
+            fill_random(&mut bytes);
+
+            let mut words = bytes
+                .chunks_exact(8)
+                .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()));
+            let mut next_key = || [words.next().unwrap(), words.next().unwrap()];
+            Ok::<_, ()>([next_key(), next_key()])
+        })
+        .ok()
+}
+
+/// Generates the SYN-ACK segment whose sequence number is a cookie, in reply to a SYN segment.
+///
+/// This method returns `None` if the secrets of cookies are not available.
+pub(crate) fn syn_ack_reply(
+    ip_repr: &IpRepr,
+    tcp_repr: &TcpRepr,
+    max_segment_size: usize,
+    now: Instant,
+) -> Option<(IpRepr, TcpRepr<'static>)> {
+    let secrets = cookie_secrets()?;
+
+    // Pick the largest MSS that does not exceed the one announced by the peer.
+    let peer_mss = tcp_repr.max_seg_size.unwrap_or(MSS_TABLE[0]);
+    let mss_index = MSS_TABLE
+        .iter()
+        .rposition(|mss| *mss <= peer_mss)
+        .unwrap_or(0);
+
+    let count = counter(now);
+    let cookie = hash(ip_repr, tcp_repr, 0, &secrets[0])
+        .wrapping_add(tcp_repr.seq_number.0 as u32)
+        .wrapping_add(count << COOKIE_BITS)
+        .wrapping_add(
+            hash(ip_repr, tcp_repr, count, &secrets[1]).wrapping_add(mss_index as u32)
+                & COOKIE_MASK,
+        );
+
+    let reply_tcp_repr = TcpRepr {
+        src_port: tcp_repr.dst_port,
+        dst_port: tcp_repr.src_port,
+        control: TcpControl::Syn,
+        seq_number: TcpSeqNumber(cookie as i32),
+        ack_number: Some(tcp_repr.seq_number + 1),
+        window_len: TCP_RECV_BUF_LEN.min(u16::MAX as usize) as u16,
+        window_scale: None,
+        max_seg_size: Some(max_segment_size.min(u16::MAX as usize) as u16),
+        sack_permitted: false,
+        sack_ranges: [None, None, None],
+        timestamp: None,
+        payload: &[],
+    };
+    let reply_ip_repr = IpRepr::new(
+        ip_repr.dst_addr(),
+        ip_repr.src_addr(),
+        IpProtocol::Tcp,
+        reply_tcp_repr.buffer_len(),
+        64,
+    );
+
+    Some((reply_ip_repr, reply_tcp_repr))
+}
+
+/// Checks the cookie echoed back in an ACK segment.
+///
+/// If the cookie is valid, this method returns the SYN segment that the cookie was generated
+/// for, with only the options that can be encoded in the cookie.
+pub(crate) fn check_ack<'a>(
+    ip_repr: &IpRepr,
+    tcp_repr: &TcpRepr<'a>,
+    now: Instant,
+) -> Option<TcpRepr<'a>> {
+    let secrets = cookie_secrets()?;
+
+    let cookie = (tcp_repr.ack_number?.0 as u32).wrapping_sub(1);
+    let peer_isn = (tcp_repr.seq_number.0 as u32).wrapping_sub(1);
+
+    let count = counter(now);
+    let cookie =
+        cookie.wrapping_sub(hash(ip_repr, tcp_repr, 0, &secrets[0]).wrapping_add(peer_isn));
+    let age = count.wrapping_sub(cookie >> COOKIE_BITS) & (u32::MAX >> COOKIE_BITS);
+    if age >= MAX_COOKIE_AGE {
+        return None;
+    }
+
+    let mss_index = cookie.wrapping_sub(hash(
+        ip_repr,
+        tcp_repr,
+        count.wrapping_sub(age),
+        &secrets[1],
+    )) & COOKIE_MASK;
+    let mss = *MSS_TABLE.get(mss_index as usize)?;
+
+    Some(TcpRepr {
+        control: TcpControl::Syn,
+        seq_number: TcpSeqNumber(peer_isn as i32),
+        ack_number: None,
+        window_scale: None,
+        max_seg_size: Some(mss),
+        sack_permitted: false,
+        sack_ranges: [None, None, None],
+        timestamp: None,
+        payload: &[],
+        ..*tcp_repr
+    })
+}
+
+/// Returns the counter that increases every minute, which limits the lifetime of cookies.
+fn counter(now: Instant) -> u32 {
+    (now.total_millis() / COUNTER_INTERVAL_MS) as u32
+}
+
+/// Hashes the addresses and ports of a segment sent by the peer.
+///
+/// Like Linux, the hash is keyed with a secret, so that the peer cannot forge cookies.
+fn hash(ip_repr: &IpRepr, tcp_repr: &TcpRepr, count: u32, key: &[u64; 2]) -> u32 {
+    let (src_hi, src_lo) = addr_words(ip_repr.src_addr());
+    let (dst_hi, dst_lo) = addr_words(ip_repr.dst_addr());
+    let ports_and_count =
+        (tcp_repr.src_port as u64) << 48 | (tcp_repr.dst_port as u64) << 32 | count as u64;
+
+    siphash(key, &[src_hi, src_lo, dst_hi, dst_lo, ports_and_count]) as u32
+}
+
+fn addr_words(addr: IpAddress) -> (u64, u64) {
+    match addr {
+        IpAddress::Ipv4(addr) => (0, addr.to_bits() as u64),
+        IpAddress::Ipv6(addr) => {
+            let bits = addr.to_bits();
+            ((bits >> 64) as u64, bits as u64)
+        }
+    }
+}
+
+/// Computes SipHash-2-4 of a message that consists of 64-bit words.
+///
+/// Reference: <https://www.aumasson.jp/siphash/siphash.pdf>.
+fn siphash(key: &[u64; 2], words: &[u64]) -> u64 {
+    let mut state = [
+        key[0] ^ 0x736f_6d65_7073_6575,
+        key[1] ^ 0x646f_7261_6e64_6f6d,
+        key[0] ^ 0x6c79_6765_6e65_7261,
+        key[1] ^ 0x7465_6462_7974_6573,
+    ];
+
+    // The last block encodes the length of the message in bytes.
+    let last_word = ((words.len() * 8) as u64) << 56;
+    for &word in words.iter().chain(core::iter::once(&last_word)) {
+        state[3] ^= word;
+        sip_round(&mut state);
+        sip_round(&mut state);
+        state[0] ^= word;
+    }
+
+    state[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut state);
+    }
+
+    state[0] ^ state[1] ^ state[2] ^ state[3]
+}
+
+fn sip_round(state: &mut [u64; 4]) {
+    let [v0, v1, v2, v3] = state;
+    *v0 = v0.wrapping_add(*v1);
+    *v1 = v1.rotate_left(13) ^ *v0;
+    *v0 = v0.rotate_left(32);
+    *v2 = v2.wrapping_add(*v3);
+    *v3 = v3.rotate_left(16) ^ *v2;
+    *v0 = v0.wrapping_add(*v3);
+    *v3 = v3.rotate_left(21) ^ *v0;
+    *v2 = v2.wrapping_add(*v1);
+    *v1 = v1.rotate_left(17) ^ *v2;
+    *v2 = v2.rotate_left(32);
+}
@@ -1,10 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
-//! This module defines the socket table, which manages all TCP and UDP sockets,
+//! This module defines the socket table, which manages all TCP, UDP, and ICMP sockets,
 //! for efficiently inserting, looking up, and removing sockets.
 
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
-use core::net::Ipv4Addr;
+use core::net::{Ipv4Addr, Ipv6Addr};
 
 use jhash::{jhash_1vals, jhash_3vals};
 use ostd::const_assert;
@@ -12,17 +12,18 @@ use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
 use crate::{
     ext::Ext,
-    socket::{TcpConnectionBg, TcpListenerBg, UdpSocketBg},
+    socket::{IcmpSocketBg, TcpConnectionBg, TcpListenerBg, UdpSocketBg},
     wire::PortNum,
 };
 
 pub type SocketHash = u32;
 
-/// A unique key for identifying a `TcpListener`.
+/// A key for identifying a `TcpListener`.
 ///
 /// Note that two `TcpListener`s cannot listen on the same address
-/// even if both sockets set SO_REUSEADDR to true,
-/// so there cannot be multiple listeners with the same `ListenerKey`.
+/// even if both sockets set SO_REUSEADDR to true.
+/// Multiple listeners can have the same `ListenerKey` only if all of them set SO_REUSEPORT to true,
+/// in which case they form a reuseport group.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ListenerKey {
     addr: IpAddress,
@@ -103,23 +104,45 @@ const fn hash_local_remote(
     remote_addr: IpAddress,
     remote_port: PortNum,
 ) -> SocketHash {
-    // FIXME: Deal with IPv6 addresses once IPv6 is supported.
-    let IpAddress::Ipv4(local_ipv4) = local_addr;
-    let IpAddress::Ipv4(remote_ipv4) = remote_addr;
+    // Following Linux, only the last 32 bits of a local IPv6 address are used, while a remote IPv6
+    // address is hashed as a whole. See
+    // <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv6/inet6_hashtables.c#L25>.
+    let local_bits = match local_addr {
+        IpAddress::Ipv4(local_ipv4) => local_ipv4.to_bits(),
+        IpAddress::Ipv6(local_ipv6) => local_ipv6.to_bits() as u32,
+    };
+    let remote_bits = match remote_addr {
+        IpAddress::Ipv4(remote_ipv4) => remote_ipv4.to_bits(),
+        IpAddress::Ipv6(remote_ipv6) => hash_ipv6_addr(remote_ipv6, HASH_SECRET),
+    };
 
     jhash_3vals(
-        local_ipv4.to_bits(),
-        remote_ipv4.to_bits(),
+        local_bits,
+        remote_bits,
         (local_port as u32).wrapping_shl(16) | remote_port as u32,
         HASH_SECRET.wrapping_add(NET_HASHMIX),
     )
 }
 
 const fn hash_addr_port(addr: IpAddress, port: PortNum) -> SocketHash {
-    // FIXME: Deal with IPv6 addresses once IPv6 is supported.
-    let IpAddress::Ipv4(ipv4_addr) = addr;
+    let addr_hash = match addr {
+        IpAddress::Ipv4(ipv4_addr) => jhash_1vals(ipv4_addr.to_bits(), NET_HASHMIX),
+        IpAddress::Ipv6(ipv6_addr) => hash_ipv6_addr(ipv6_addr, NET_HASHMIX),
+    };
 
-    jhash_1vals(ipv4_addr.to_bits(), NET_HASHMIX) ^ (port as u32)
+    addr_hash ^ (port as u32)
+}
+
+/// Hashes an IPv6 address in the same way as Linux's `__ipv6_addr_jhash`.
+const fn hash_ipv6_addr(addr: Ipv6Addr, initval: u32) -> u32 {
+    let bits = addr.to_bits();
+
+    jhash_3vals(
+        (bits >> 96) as u32 ^ (bits >> 64) as u32,
+        (bits >> 32) as u32,
+        bits as u32,
+        initval,
+    )
 }
 
 /// The socket table manages TCP and UDP sockets.
@@ -142,6 +165,9 @@ pub(crate) struct SocketTable<E: Ext> {
     // Note that multiple UDP sockets can be bound to the same address,
     // so we cannot use (addr, port) as a _unique_ key for UDP sockets.
     udp_sockets: Vec<Arc<UdpSocketBg<E>>>,
+    // Similarly, ICMP sockets are included in the socket table. They are identified by the ICMP
+    // identifiers, which are stored as the port numbers.
+    icmp_sockets: Vec<Arc<IcmpSocketBg<E>>>,
 }
 
 // On Linux, the number of buckets is determined at runtime based on the available memory.
@@ -166,38 +192,52 @@ impl<E: Ext> SocketTable<E> {
             .collect();
 
         let udp_sockets = Vec::new();
+        let icmp_sockets = Vec::new();
 
         Self {
             listener_buckets,
             connection_buckets,
             udp_sockets,
+            icmp_sockets,
         }
     }
 
+    /// Checks whether a TCP listener with the [`ListenerKey`] can be inserted into the table.
+    ///
+    /// This method will return false if a listener with the same [`ListenerKey`] has already been
+    /// inserted, unless both listeners set SO_REUSEPORT to true.
+    pub(crate) fn can_insert_listener(&self, key: &ListenerKey, reuse_port: bool) -> bool {
+        let bucket = {
+            let hash = key.hash();
+            let bucket_index = hash & LISTENER_BUCKET_MASK;
+            &self.listener_buckets[bucket_index as usize]
+        };
+
+        bucket
+            .listeners
+            .iter()
+            .filter(|tcp_listener| tcp_listener.listener_key() == key)
+            .all(|tcp_listener| reuse_port && tcp_listener.reuse_port())
+    }
+
     /// Inserts a TCP listener into the table.
     ///
-    /// If a socket with the same [`ListenerKey`] has already been inserted,
+    /// If the listener cannot be inserted (see [`Self::can_insert_listener`]),
     /// this method will return an error and the listener will not be inserted.
     pub(crate) fn insert_listener(
         &mut self,
         listener: Arc<TcpListenerBg<E>>,
     ) -> Result<(), Arc<TcpListenerBg<E>>> {
-        let key = listener.listener_key();
+        if !self.can_insert_listener(listener.listener_key(), listener.reuse_port()) {
+            return Err(listener);
+        }
 
         let bucket = {
-            let hash = key.hash();
+            let hash = listener.listener_key().hash();
             let bucket_index = hash & LISTENER_BUCKET_MASK;
             &mut self.listener_buckets[bucket_index as usize]
         };
 
-        if bucket
-            .listeners
-            .iter()
-            .any(|tcp_listener| tcp_listener.listener_key() == listener.listener_key())
-        {
-            return Err(listener);
-        }
-
         bucket.listeners.push(listener);
         Ok(())
     }
@@ -234,17 +274,43 @@ impl<E: Ext> SocketTable<E> {
         self.udp_sockets.push(udp_socket);
     }
 
-    pub(crate) fn lookup_listener(&self, key: &ListenerKey) -> Option<&Arc<TcpListenerBg<E>>> {
+    pub(crate) fn insert_icmp_socket(&mut self, icmp_socket: Arc<IcmpSocketBg<E>>) {
+        debug_assert!(!self
+            .icmp_sockets
+            .iter()
+            .any(|socket| Arc::ptr_eq(socket, &icmp_socket)));
+        self.icmp_sockets.push(icmp_socket);
+    }
+
+    /// Looks up the TCP listener that should accept the new connection.
+    ///
+    /// If multiple listeners form a reuseport group, one of them is selected by the hash of the
+    /// [`ConnectionKey`]. This distributes new connections evenly across the listeners, similar to
+    /// Linux's `reuseport_select_sock`.
+    pub(crate) fn lookup_listener(
+        &self,
+        key: &ListenerKey,
+        connection_key: &ConnectionKey,
+    ) -> Option<&Arc<TcpListenerBg<E>>> {
         let bucket = {
             let hash = key.hash();
             let bucket_index = hash & LISTENER_BUCKET_MASK;
             &self.listener_buckets[bucket_index as usize]
         };
 
-        bucket
+        let mut group = bucket
             .listeners
             .iter()
-            .find(|listener| listener.listener_key() == key)
+            .filter(|listener| listener.listener_key() == key);
+
+        let group_len = group.clone().count();
+        if group_len <= 1 {
+            return group.next();
+        }
+
+        // This maps the hash to `0..group_len` in the same way as Linux's `reciprocal_scale`.
+        let index = ((connection_key.hash() as u64 * group_len as u64) >> 32) as usize;
+        group.nth(index)
     }
 
     pub(crate) fn lookup_connection(
@@ -263,17 +329,22 @@ impl<E: Ext> SocketTable<E> {
             .find(|connection| connection.connection_key() == key)
     }
 
-    pub(crate) fn remove_listener(&mut self, key: &ListenerKey) -> Option<Arc<TcpListenerBg<E>>> {
+    pub(crate) fn remove_listener(
+        &mut self,
+        listener: &Arc<TcpListenerBg<E>>,
+    ) -> Option<Arc<TcpListenerBg<E>>> {
         let bucket = {
-            let hash = key.hash();
+            let hash = listener.listener_key().hash();
             let bucket_index = hash & LISTENER_BUCKET_MASK;
             &mut self.listener_buckets[bucket_index as usize]
         };
 
+        // Listeners in a reuseport group have the same key, so the listener must be compared by
+        // its address.
         let index = bucket
             .listeners
             .iter()
-            .position(|tcp_listener| tcp_listener.listener_key() == key)?;
+            .position(|tcp_listener| Arc::ptr_eq(tcp_listener, listener))?;
         Some(bucket.listeners.swap_remove(index))
     }
 
@@ -312,6 +383,21 @@ impl<E: Ext> SocketTable<E> {
     pub(crate) fn udp_socket_iter(&self) -> impl Iterator<Item = &Arc<UdpSocketBg<E>>> {
         self.udp_sockets.iter()
     }
+
+    pub(crate) fn remove_icmp_socket(
+        &mut self,
+        socket: &Arc<IcmpSocketBg<E>>,
+    ) -> Option<Arc<IcmpSocketBg<E>>> {
+        let index = self
+            .icmp_sockets
+            .iter()
+            .position(|icmp_socket| Arc::ptr_eq(icmp_socket, socket))?;
+        Some(self.icmp_sockets.swap_remove(index))
+    }
+
+    pub(crate) fn icmp_socket_iter(&self) -> impl Iterator<Item = &Arc<IcmpSocketBg<E>>> {
+        self.icmp_sockets.iter()
+    }
 }
 
 impl<E: Ext> Default for SocketTable<E> {
@@ -1,3 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub use smoltcp::time::{Duration, Instant};
+
+/// A trait for sockets to timestamp the packets that are sent or received.
+pub trait PacketClock {
+    /// Returns the current time, which is used as the timestamp of a packet.
+    ///
+    /// Unlike [`Instant`], which is only used by the network stack internally, the returned time
+    /// is reported to the users, so it should be read from the clock that the users expect
+    /// (e.g., the real-time clock).
+    fn now() -> core::time::Duration;
+}
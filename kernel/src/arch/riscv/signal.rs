@@ -2,7 +2,12 @@
 
 use ostd::cpu::context::{CpuExceptionInfo, UserContext};
 
-use crate::process::signal::{sig_num::SigNum, signals::fault::FaultSignal, SignalContext};
+use crate::{
+    prelude::*,
+    process::signal::{
+        sig_num::SigNum, signals::fault::FaultSignal, SigFpstateLayout, SignalContext,
+    },
+};
 
 impl SignalContext for UserContext {
     fn set_arguments(&mut self, sig_num: SigNum, siginfo_addr: usize, ucontext_addr: usize) {
@@ -10,6 +15,20 @@ impl SignalContext for UserContext {
         self.set_a1(siginfo_addr);
         self.set_a2(ucontext_addr);
     }
+
+    // FIXME: Save and restore the FPU state once it is implemented on RISC-V platforms.
+
+    fn fpstate_layout(&self) -> SigFpstateLayout {
+        SigFpstateLayout { size: 0, align: 16 }
+    }
+
+    fn save_fpstate(&self, _addr: Vaddr, _user_space: &CurrentUserSpace) -> Result<()> {
+        Ok(())
+    }
+
+    fn restore_fpstate(&mut self, _addr: Vaddr, _user_space: &CurrentUserSpace) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl From<&CpuExceptionInfo> for FaultSignal {
@@ -2,8 +2,11 @@
 
 use ostd::cpu::context::{CpuException, CpuExceptionInfo, UserContext};
 
-use crate::process::signal::{
-    constants::*, sig_num::SigNum, signals::fault::FaultSignal, SignalContext,
+use crate::{
+    prelude::*,
+    process::signal::{
+        constants::*, sig_num::SigNum, signals::fault::FaultSignal, SigFpstateLayout, SignalContext,
+    },
 };
 
 impl SignalContext for UserContext {
@@ -12,6 +15,115 @@ impl SignalContext for UserContext {
         self.set_rsi(siginfo_addr);
         self.set_rdx(ucontext_addr);
     }
+
+    fn fpstate_layout(&self) -> SigFpstateLayout {
+        let size = self.fpu_state().size();
+        SigFpstateLayout {
+            // The `XSAVE` area is followed by `FP_XSTATE_MAGIC2`.
+            size: if size > FXSAVE_AREA_SIZE {
+                size + size_of::<u32>()
+            } else {
+                size
+            },
+            align: FPSTATE_ALIGN,
+        }
+    }
+
+    fn save_fpstate(&self, addr: Vaddr, user_space: &CurrentUserSpace) -> Result<()> {
+        let fpu_state = self.fpu_state();
+        fpu_state.save();
+
+        let bytes = fpu_state.as_bytes();
+        user_space.write_bytes(addr, &mut VmReader::from(&bytes[..FXSAVE_SW_BYTES_OFFSET]))?;
+
+        // Without `XSAVE`, the software reserved bytes are left zero, which tells the user
+        // space that there are no extended states.
+        let sw_bytes = if bytes.len() > FXSAVE_AREA_SIZE {
+            FpxSwBytes {
+                magic1: FP_XSTATE_MAGIC1,
+                extended_size: (bytes.len() + size_of::<u32>()) as u32,
+                xfeatures: fpu_state.xfeatures(),
+                xstate_size: bytes.len() as u32,
+                padding: [0; 7],
+            }
+        } else {
+            FpxSwBytes::new_zeroed()
+        };
+        user_space.write_val(addr + FXSAVE_SW_BYTES_OFFSET, &sw_bytes)?;
+
+        if bytes.len() > FXSAVE_AREA_SIZE {
+            user_space.write_bytes(
+                addr + FXSAVE_AREA_SIZE,
+                &mut VmReader::from(&bytes[FXSAVE_AREA_SIZE..]),
+            )?;
+            user_space.write_val(addr + bytes.len(), &FP_XSTATE_MAGIC2)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore_fpstate(&mut self, addr: Vaddr, user_space: &CurrentUserSpace) -> Result<()> {
+        if addr % FPSTATE_ALIGN != 0 {
+            return_errno_with_message!(Errno::EFAULT, "the FPU state is not aligned");
+        }
+
+        // The extended states are present only if the software reserved bytes say so.
+        // Otherwise, only the legacy area is restored, as Linux does.
+        let size = self.fpu_state().size();
+        let sw_bytes: FpxSwBytes = user_space.read_val(addr + FXSAVE_SW_BYTES_OFFSET)?;
+        let len = if size > FXSAVE_AREA_SIZE
+            && sw_bytes.magic1 == FP_XSTATE_MAGIC1
+            && sw_bytes.xstate_size as usize == size
+            && sw_bytes.extended_size as usize == size + size_of::<u32>()
+            && user_space.read_val::<u32>(addr + size)? == FP_XSTATE_MAGIC2
+        {
+            size
+        } else {
+            FXSAVE_AREA_SIZE
+        };
+
+        let fpu_state = self.fpu_state_mut();
+        user_space.read_bytes(
+            addr,
+            &mut VmWriter::from(&mut fpu_state.as_bytes_mut()[..len]),
+        )?;
+        fpu_state
+            .validate(len)
+            .map_err(|_| Error::with_message(Errno::EFAULT, "the FPU state is invalid"))?;
+        // Load the state immediately, so that it will not be overwritten when the FPU state is
+        // saved again (e.g., for a nested signal or a context switch).
+        fpu_state.restore();
+
+        Ok(())
+    }
+}
+
+/// The alignment of the FPU state in the signal frames, which is required by `XSAVE`.
+const FPSTATE_ALIGN: usize = 64;
+
+/// The size of the legacy `FXSAVE` area.
+const FXSAVE_AREA_SIZE: usize = 512;
+
+/// The offset of [`FpxSwBytes`] in the legacy `FXSAVE` area.
+const FXSAVE_SW_BYTES_OFFSET: usize = 464;
+
+const FP_XSTATE_MAGIC1: u32 = 0x46505853;
+const FP_XSTATE_MAGIC2: u32 = 0x46505845;
+
+/// The software reserved bytes in the legacy `FXSAVE` area, which describe the
+/// extended states in the signal frames.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/arch/x86/include/uapi/asm/sigcontext.h#L27>
+#[derive(Clone, Copy, Debug, Pod)]
+#[repr(C)]
+struct FpxSwBytes {
+    magic1: u32,
+    /// The size of the extended states, including `FP_XSTATE_MAGIC2`.
+    extended_size: u32,
+    xfeatures: u64,
+    /// The size of the `XSAVE` area.
+    xstate_size: u32,
+    padding: [u32; 7],
 }
 
 impl From<&CpuExceptionInfo> for FaultSignal {
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device files of the registered block devices and their partitions (e.g., `/dev/vext2`
+//! and `/dev/vext2p1`).
+//!
+//! The block devices are registered by their drivers, and the partitions are registered after
+//! the partition table of a device is scanned, e.g., with the `BLKRRPART` ioctl. The device files
+//! are kept in sync with the registered block devices.
+
+use core::ops::Range;
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus},
+    id::Sid,
+    BlockDevice, BLOCK_MAJOR, BLOCK_SIZE, SECTOR_SIZE,
+};
+use ostd::mm::VmIo;
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_devtmpfs_node, delete_devtmpfs_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The device files, which are indexed by their minor device numbers.
+static DISK_FILES: Mutex<BTreeMap<u32, Arc<DiskFile>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init() -> Result<()> {
+    sync_nodes();
+    Ok(())
+}
+
+/// Returns the device file of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(file) = DISK_FILES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the block device does not exist");
+    };
+    Ok(file)
+}
+
+/// Opens the block device of the minor device number.
+pub(super) fn open_block_device(minor: u32) -> Result<Arc<dyn BlockDevice>> {
+    let Some(file) = DISK_FILES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENXIO, "the block device does not exist");
+    };
+    file.block_device()
+}
+
+/// Scans the partition table of the block device, and updates the device files of its
+/// partitions.
+pub fn scan_partitions(name: &str) -> Result<()> {
+    aster_block::scan_partitions(name)?;
+    sync_nodes();
+    Ok(())
+}
+
+/// Adds the device files of the new block devices, and removes those of the removed ones.
+fn sync_nodes() {
+    let mut files = DISK_FILES.lock();
+
+    files.retain(|minor, file| {
+        if aster_block::device_minor(&file.name) == Some(*minor) {
+            return true;
+        }
+        if let Err(err) = delete_devtmpfs_node(&file.name) {
+            warn!(
+                "failed to delete the device file of {}: {:?}",
+                file.name, err
+            );
+        }
+        false
+    });
+
+    for (name, _) in aster_block::all_devices() {
+        let Some(minor) = aster_block::device_minor(&name) else {
+            continue;
+        };
+        if files.contains_key(&minor) {
+            continue;
+        }
+
+        let file = Arc::new(DiskFile { name, minor });
+        if let Err(err) = add_devtmpfs_node(file.clone(), &file.name) {
+            warn!("failed to add the device file of {}: {:?}", file.name, err);
+            continue;
+        }
+        files.insert(minor, file);
+    }
+}
+
+/// The device file of a registered block device.
+///
+/// The block device is looked up by its name when the file is opened, so that the file does not
+/// keep the device in use (e.g., a partition that is removed after the partition table is
+/// scanned again).
+struct DiskFile {
+    name: String,
+    minor: u32,
+}
+
+impl DiskFile {
+    fn block_device(&self) -> Result<Arc<dyn BlockDevice>> {
+        let Some(device) = aster_block::get_device(&self.name) else {
+            return_errno_with_message!(Errno::ENXIO, "the block device does not exist");
+        };
+        Ok(device)
+    }
+}
+
+impl Device for DiskFile {
+    fn type_(&self) -> DeviceType {
+        DeviceType::BlockDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(BLOCK_MAJOR, self.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let handle = DiskHandle {
+            name: self.name.clone(),
+            device: self.block_device()?,
+        };
+        Ok(Some(Arc::new(handle)))
+    }
+}
+
+impl Pollable for DiskFile {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for DiskFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the block device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the block device is not opened");
+    }
+}
+
+/// An opened device file of a block device.
+struct DiskHandle {
+    name: String,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl Pollable for DiskHandle {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for DiskHandle {
+    // TODO: Support reading and writing the block devices directly. `FileIo` does not know the
+    // file offset, so only the ioctls are supported now.
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the block device cannot be read directly");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the block device cannot be written directly");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let nr_sectors = self.device.metadata().nr_sectors;
+
+        match cmd {
+            IoctlCmd::BLKRRPART => scan_partitions(&self.name)?,
+            IoctlCmd::BLKGETSIZE => current_userspace!().write_val(arg, &(nr_sectors as u64))?,
+            IoctlCmd::BLKSSZGET => current_userspace!().write_val(arg, &(SECTOR_SIZE as i32))?,
+            IoctlCmd::BLKGETSIZE64 => {
+                current_userspace!().write_val(arg, &((nr_sectors * SECTOR_SIZE) as u64))?
+            }
+            IoctlCmd::BLKDISCARD => {
+                if self.device.max_discard_sectors() == 0 {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the block device does not support discarding"
+                    );
+                }
+                let sid_range = read_sector_range(arg, nr_sectors)?;
+                match self.device.discard(sid_range) {
+                    Ok(BioStatus::NotSupported) => {
+                        return_errno_with_message!(
+                            Errno::EOPNOTSUPP,
+                            "the block device does not support discarding"
+                        );
+                    }
+                    result => check_bio_status(result)?,
+                }
+            }
+            IoctlCmd::BLKZEROOUT => {
+                let sid_range = read_sector_range(arg, nr_sectors)?;
+                if sid_range.is_empty() {
+                    return_errno_with_message!(Errno::EINVAL, "the range is empty");
+                }
+                self.zero_out(sid_range)?;
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+        Ok(0)
+    }
+}
+
+impl DiskHandle {
+    /// Writes zeroes to the sectors.
+    ///
+    /// If the block device cannot write zeroes without data, the zeroes are written as data.
+    fn zero_out(&self, sid_range: Range<Sid>) -> Result<()> {
+        match self.device.write_zeroes(sid_range.clone()) {
+            Ok(BioStatus::NotSupported) => {}
+            result => return check_bio_status(result),
+        }
+
+        const ZEROES_LEN: usize = BLOCK_SIZE * 16;
+        let zeroes = vec![0u8; ZEROES_LEN];
+        let mut offset = sid_range.start.to_offset();
+        let end = sid_range.end.to_offset();
+        while offset < end {
+            let len = (end - offset).min(ZEROES_LEN);
+            self.device.write_bytes(offset, &zeroes[..len])?;
+            offset += len;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the range of bytes of the `BLKDISCARD` and `BLKZEROOUT` ioctls, which is an array
+/// of the start and the length, and converts it to a range of sectors.
+fn read_sector_range(arg: usize, nr_sectors: usize) -> Result<Range<Sid>> {
+    let start: u64 = current_userspace!().read_val(arg)?;
+    let len: u64 = current_userspace!().read_val(arg + size_of::<u64>())?;
+
+    if start % SECTOR_SIZE as u64 != 0 || len % SECTOR_SIZE as u64 != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the range is not aligned to sectors");
+    }
+    let Some(end) = start.checked_add(len) else {
+        return_errno_with_message!(Errno::EINVAL, "the range overflows");
+    };
+    if end > (nr_sectors * SECTOR_SIZE) as u64 {
+        return_errno_with_message!(Errno::EINVAL, "the range exceeds the block device");
+    }
+
+    Ok(Sid::from_offset(start as usize)..Sid::from_offset(end as usize))
+}
+
+fn check_bio_status(result: core::result::Result<BioStatus, BioEnqueueError>) -> Result<()> {
+    match result {
+        Ok(BioStatus::Complete) => Ok(()),
+        Ok(BioStatus::NoSpace) => return_errno_with_message!(Errno::ENOSPC, "no space left"),
+        _ => return_errno_with_message!(Errno::EIO, "the I/O failed"),
+    }
+}
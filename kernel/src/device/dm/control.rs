@@ -0,0 +1,342 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device mapper control device (`/dev/mapper/control`).
+//!
+//! Each ioctl takes a `struct dm_ioctl`, which is followed by the data of the command (e.g., the
+//! targets of a table) in the same buffer. The header is written back with the status of the
+//! device after the command.
+//!
+//! Reference: <https://github.com/torvalds/linux/blob/master/include/uapi/linux/dm-ioctl.h>
+
+use core::{mem::size_of, sync::atomic::Ordering};
+
+use super::{
+    create_device, lookup_device, remove_all_devices, remove_device, table::Table, MappedDevice,
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The minor device number of `/dev/mapper/control`, whose major device number is 10.
+pub(in crate::device) const DM_CTRL_MINOR: u32 = 236;
+
+/// The version of the interface, which is the version of Linux 6.12.
+const DM_VERSION: [u32; 3] = [4, 48, 0];
+
+/// The maximum size of the buffer of an ioctl.
+const MAX_DATA_SIZE: usize = 1024 * 1024;
+
+const DM_NAME_LEN: usize = 128;
+const DM_UUID_LEN: usize = 129;
+const DM_MAX_TYPE_NAME: usize = 16;
+
+/// The device mapper control device, which creates, removes, and configures the mapped devices.
+pub(in crate::device) struct DmControl;
+
+impl Device for DmControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, DM_CTRL_MINOR)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(DmControl)))
+    }
+}
+
+impl Pollable for DmControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for DmControl {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the control device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the control device cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let mut param: c_dm_ioctl = current_userspace!().read_val(arg)?;
+        if param.version[0] != DM_VERSION[0] {
+            return_errno_with_message!(Errno::EINVAL, "the interface version is not supported");
+        }
+        let data_size = param.data_size as usize;
+        if !(size_of::<c_dm_ioctl>()..=MAX_DATA_SIZE).contains(&data_size) {
+            return_errno_with_message!(Errno::EINVAL, "the buffer size is invalid");
+        }
+
+        param.version = DM_VERSION;
+        param.data_size = size_of::<c_dm_ioctl>() as u32;
+
+        match cmd {
+            IoctlCmd::DM_VERSION => (),
+            IoctlCmd::DM_REMOVE_ALL => remove_all_devices(),
+            IoctlCmd::DM_DEV_CREATE => {
+                let minor = DmFlags::from_bits_truncate(param.flags)
+                    .contains(DmFlags::PERSISTENT_DEV)
+                    .then(|| DeviceId::from(param.dev).minor());
+                let device = create_device(param.name()?, param.uuid()?, minor)?;
+                param.set_status(&device);
+            }
+            IoctlCmd::DM_DEV_REMOVE => {
+                let device = param.lookup_device()?;
+                remove_device(&device)?;
+            }
+            IoctlCmd::DM_DEV_SUSPEND => {
+                let device = param.lookup_device()?;
+                if DmFlags::from_bits_truncate(param.flags).contains(DmFlags::SUSPEND) {
+                    device.suspend();
+                } else {
+                    device.resume();
+                }
+                param.set_status(&device);
+            }
+            IoctlCmd::DM_DEV_STATUS => {
+                let device = param.lookup_device()?;
+                param.set_status(&device);
+            }
+            IoctlCmd::DM_TABLE_LOAD => {
+                let device = param.lookup_device()?;
+                let mut buf = vec![0; data_size];
+                current_userspace!().read_bytes(arg, &mut VmWriter::from(buf.as_mut_slice()))?;
+                device.load_table(param.parse_table(&buf)?);
+                param.set_status(&device);
+            }
+            IoctlCmd::DM_TABLE_CLEAR => {
+                let device = param.lookup_device()?;
+                device.clear_table();
+                param.set_status(&device);
+            }
+            IoctlCmd::DM_TABLE_STATUS => {
+                let device = param.lookup_device()?;
+                param.set_status(&device);
+
+                let data = param.format_table(&device);
+                param.data_start = size_of::<c_dm_ioctl>() as u32;
+                if param.data_start as usize + data.len() > data_size {
+                    param.flags |= DmFlags::BUFFER_FULL.bits();
+                } else {
+                    current_userspace!().write_bytes(
+                        arg + param.data_start as usize,
+                        &mut VmReader::from(data.as_slice()),
+                    )?;
+                }
+                param.data_size = param.data_start + data.len() as u32;
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        current_userspace!().write_val(arg, &param)?;
+        Ok(0)
+    }
+}
+
+bitflags! {
+    struct DmFlags: u32 {
+        const READONLY             = 1 << 0;
+        const SUSPEND              = 1 << 1;
+        const PERSISTENT_DEV       = 1 << 3;
+        const STATUS_TABLE         = 1 << 4;
+        const ACTIVE_PRESENT       = 1 << 5;
+        const INACTIVE_PRESENT     = 1 << 6;
+        const BUFFER_FULL          = 1 << 8;
+        const QUERY_INACTIVE_TABLE = 1 << 12;
+    }
+}
+
+impl DmFlags {
+    /// The flags that describe the status of a device.
+    const STATUS: Self = Self::READONLY
+        .union(Self::SUSPEND)
+        .union(Self::ACTIVE_PRESENT)
+        .union(Self::INACTIVE_PRESENT)
+        .union(Self::BUFFER_FULL);
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/dm-ioctl.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_dm_ioctl {
+    version: [u32; 3],
+    data_size: u32,
+    data_start: u32,
+    target_count: u32,
+    open_count: i32,
+    flags: u32,
+    event_nr: u32,
+    padding: u32,
+    dev: u64,
+    name: [u8; DM_NAME_LEN],
+    uuid: [u8; DM_UUID_LEN],
+    data: [u8; 7],
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/dm-ioctl.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_dm_target_spec {
+    sector_start: u64,
+    length: u64,
+    status: i32,
+    next: u32,
+    target_type: [u8; DM_MAX_TYPE_NAME],
+}
+
+impl c_dm_ioctl {
+    fn name(&self) -> Result<&str> {
+        parse_c_str(&self.name)
+    }
+
+    fn uuid(&self) -> Result<&str> {
+        parse_c_str(&self.uuid)
+    }
+
+    fn lookup_device(&self) -> Result<Arc<MappedDevice>> {
+        lookup_device(self.name()?, self.uuid()?, DeviceId::from(self.dev))
+    }
+
+    /// Fills in the status of the device.
+    ///
+    /// The table is the inactive one if `DM_QUERY_INACTIVE_TABLE_FLAG` is set, or the active
+    /// one otherwise.
+    fn set_status(&mut self, device: &MappedDevice) {
+        let mut flags = DmFlags::from_bits_truncate(self.flags) - DmFlags::STATUS;
+
+        let state = device.state.lock();
+        if state.is_suspended {
+            flags |= DmFlags::SUSPEND;
+        }
+        if state.active.is_some() {
+            flags |= DmFlags::ACTIVE_PRESENT;
+        }
+        if state.inactive.is_some() {
+            flags |= DmFlags::INACTIVE_PRESENT;
+        }
+        let table = if flags.contains(DmFlags::QUERY_INACTIVE_TABLE) {
+            state.inactive.as_ref()
+        } else {
+            state.active.as_ref()
+        };
+        if table.is_some_and(|table| table.is_read_only()) {
+            flags |= DmFlags::READONLY;
+        }
+        self.target_count = table.map_or(0, |table| table.targets().len() as u32);
+        drop(state);
+
+        self.flags = flags.bits();
+        self.open_count = device.nr_openers.load(Ordering::Relaxed) as i32;
+        self.dev = device.id().into();
+        self.name = [0; DM_NAME_LEN];
+        self.name[..device.name.len()].copy_from_slice(device.name.as_bytes());
+        self.uuid = [0; DM_UUID_LEN];
+        self.uuid[..device.uuid.len()].copy_from_slice(device.uuid.as_bytes());
+    }
+
+    /// Parses the table in the buffer, which starts with this header.
+    ///
+    /// Each target is a `struct dm_target_spec` followed by its parameters. The first target
+    /// starts at `data_start`, and each target is followed by the next one at `next` bytes
+    /// after it.
+    fn parse_table(&self, buf: &[u8]) -> Result<Table> {
+        if self.target_count == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the table has no targets");
+        }
+
+        let is_read_only = DmFlags::from_bits_truncate(self.flags).contains(DmFlags::READONLY);
+        let mut table = Table::new(is_read_only);
+
+        let mut spec_offset = self.data_start as usize;
+        let mut min_offset = size_of::<c_dm_ioctl>();
+        for _ in 0..self.target_count {
+            let params_offset = spec_offset + size_of::<c_dm_target_spec>();
+            if spec_offset < min_offset || params_offset > buf.len() {
+                return_errno_with_message!(Errno::EINVAL, "the target is out of the buffer");
+            }
+
+            let spec = c_dm_target_spec::from_bytes(&buf[spec_offset..params_offset]);
+            let params = parse_c_str(&buf[params_offset..])?;
+            table.add_target(
+                spec.sector_start as usize,
+                spec.length as usize,
+                parse_c_str(&spec.target_type)?,
+                params,
+            )?;
+
+            min_offset = params_offset;
+            spec_offset += spec.next as usize;
+        }
+
+        Ok(table)
+    }
+
+    /// Formats the targets of the table, with their parameters if `DM_STATUS_TABLE_FLAG` is set,
+    /// or with their status otherwise.
+    ///
+    /// Unlike the input of `DM_TABLE_LOAD`, the `next` field of each target is the offset of
+    /// the next target from the first one.
+    fn format_table(&self, device: &MappedDevice) -> Vec<u8> {
+        let flags = DmFlags::from_bits_truncate(self.flags);
+
+        let state = device.state.lock();
+        let table = if flags.contains(DmFlags::QUERY_INACTIVE_TABLE) {
+            state.inactive.clone()
+        } else {
+            state.active.clone()
+        };
+        drop(state);
+        let Some(table) = table else {
+            return Vec::new();
+        };
+
+        let mut data = Vec::new();
+        for target in table.targets() {
+            let spec_offset = data.len();
+            data.resize(spec_offset + size_of::<c_dm_target_spec>(), 0);
+
+            let info = if flags.contains(DmFlags::STATUS_TABLE) {
+                target.target.params()
+            } else {
+                target.target.status()
+            };
+            data.extend_from_slice(info.as_bytes());
+            data.push(0);
+            data.resize(data.len().next_multiple_of(8), 0);
+
+            let mut spec = c_dm_target_spec {
+                sector_start: target.start as u64,
+                length: target.len as u64,
+                status: 0,
+                next: data.len() as u32,
+                target_type: [0; DM_MAX_TYPE_NAME],
+            };
+            spec.target_type[..target.type_name.len()].copy_from_slice(target.type_name.as_bytes());
+            data[spec_offset..spec_offset + size_of::<c_dm_target_spec>()]
+                .copy_from_slice(spec.as_bytes());
+        }
+        data
+    }
+}
+
+/// Parses a NUL-terminated string in the buffer.
+fn parse_c_str(buf: &[u8]) -> Result<&str> {
+    let Some(len) = buf.iter().position(|&byte| byte == 0) else {
+        return_errno_with_message!(Errno::EINVAL, "the string is not terminated");
+    };
+    core::str::from_utf8(&buf[..len])
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the string is not valid UTF-8"))
+}
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `linear` target, which maps the sectors to a range of another device.
+//!
+//! The parameters are `<device> <start sector>`.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/device-mapper/linear.html>
+
+use aster_block::SECTOR_SIZE;
+
+use super::table::{Target, TargetDevice};
+use crate::prelude::*;
+
+pub(super) struct LinearTarget {
+    device: TargetDevice,
+    start: usize,
+}
+
+impl LinearTarget {
+    pub(super) fn new(args: &[&str]) -> Result<Self> {
+        let [device, start] = args else {
+            return_errno_with_message!(Errno::EINVAL, "the linear target needs two parameters");
+        };
+        let start = start
+            .parse()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the start sector is invalid"))?;
+
+        Ok(Self {
+            device: TargetDevice::open(device)?,
+            start,
+        })
+    }
+
+    fn offset(&self, sector: usize) -> usize {
+        (self.start + sector) * SECTOR_SIZE
+    }
+}
+
+impl Target for LinearTarget {
+    fn read(&self, sector: usize, buf: &mut [u8]) -> Result<()> {
+        self.device.read(self.offset(sector), buf)
+    }
+
+    fn write(&self, sector: usize, buf: &[u8]) -> Result<()> {
+        self.device.write(self.offset(sector), buf)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.device.flush()
+    }
+
+    fn status(&self) -> String {
+        String::new()
+    }
+
+    fn params(&self) -> String {
+        format!("{} {}", self.device, self.start)
+    }
+}
@@ -0,0 +1,335 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device mapper, which maps the sectors of the mapped devices (`/dev/dm-N`) to other block
+//! devices with tables of targets.
+//!
+//! The mapped devices are created and given tables with the ioctls on `/dev/mapper/control`
+//! (see [`control`]), which are used by `dmsetup` and `veritysetup`. A table is loaded as the
+//! inactive table first, and becomes active when the device is resumed.
+//!
+//! The supported targets are:
+//!  - `linear`, which maps the sectors to a range of another device (see [`linear`]);
+//!  - `verity`, which verifies the sectors of a read-only device against a hash tree
+//!    (see [`verity`]).
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/device-mapper/index.html>
+
+mod control;
+mod linear;
+mod sha256;
+mod table;
+mod verity;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, SubmittedBio},
+    BlockDevice, BlockDeviceMeta,
+};
+use ostd::sync::WaitQueue;
+
+pub(super) use self::control::{DmControl, DM_CTRL_MINOR};
+use self::table::Table;
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, delete_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the mapped devices.
+///
+/// Linux allocates the number dynamically, so the number that Linux usually gets is used.
+pub(super) const DM_MAJOR: u32 = 253;
+
+/// The maximum number of segments in a bio.
+///
+/// The bios are served synchronously, so the limit only bounds the memory used by a bio.
+const MAX_NR_SEGMENTS_PER_BIO: usize = 128;
+
+/// The mapped devices, which are indexed by their minor device numbers.
+static MAPPED_DEVICES: Mutex<BTreeMap<u32, Arc<MappedDevice>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init() -> Result<()> {
+    add_node(Arc::new(DmControl), "mapper/control", "misc")?;
+    Ok(())
+}
+
+/// Returns the mapped device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = MAPPED_DEVICES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the mapped device does not exist");
+    };
+    Ok(device)
+}
+
+/// Opens the mapped device of the minor device number as a block device.
+///
+/// The device cannot be removed until the returned block device is dropped.
+pub(super) fn open_block_device(minor: u32) -> Result<Arc<dyn BlockDevice>> {
+    let Some(device) = MAPPED_DEVICES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENXIO, "the mapped device does not exist");
+    };
+    Ok(Arc::new(DmHandle::new(device)))
+}
+
+/// Creates a mapped device with the name and the UUID.
+///
+/// The device gets the minor device number if it is given, or the smallest unused one.
+fn create_device(name: &str, uuid: &str, minor: Option<u32>) -> Result<Arc<MappedDevice>> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return_errno_with_message!(Errno::EINVAL, "the device name is invalid");
+    }
+
+    let mut devices = MAPPED_DEVICES.lock();
+    if devices
+        .values()
+        .any(|device| device.name == name || (!uuid.is_empty() && device.uuid == uuid))
+    {
+        return_errno_with_message!(Errno::EBUSY, "the device name or UUID is used");
+    }
+    let minor = match minor {
+        Some(minor) if devices.contains_key(&minor) => {
+            return_errno_with_message!(Errno::EBUSY, "the minor device number is used");
+        }
+        Some(minor) => minor,
+        None => (0..).find(|minor| !devices.contains_key(minor)).unwrap(),
+    };
+
+    let device = Arc::new_cyclic(|weak_self| MappedDevice {
+        minor,
+        name: name.to_string(),
+        uuid: uuid.to_string(),
+        state: SpinLock::new(DeviceState {
+            active: None,
+            inactive: None,
+            is_suspended: false,
+        }),
+        resume_queue: WaitQueue::new(),
+        nr_openers: AtomicUsize::new(0),
+        weak_self: weak_self.clone(),
+    });
+    add_node(device.clone(), &format!("dm-{}", minor), "block")?;
+
+    devices.insert(minor, device.clone());
+    Ok(device)
+}
+
+/// Removes the mapped device, which must not be open.
+fn remove_device(device: &MappedDevice) -> Result<()> {
+    let mut devices = MAPPED_DEVICES.lock();
+
+    if device.nr_openers.load(Ordering::Relaxed) > 0 {
+        return_errno_with_message!(Errno::EBUSY, "the mapped device is in use");
+    }
+
+    delete_node(&format!("dm-{}", device.minor), "block")?;
+    devices.remove(&device.minor);
+    Ok(())
+}
+
+/// Removes all the mapped devices that are not open.
+fn remove_all_devices() {
+    let devices: Vec<_> = MAPPED_DEVICES.lock().values().cloned().collect();
+    for device in devices {
+        let _ = remove_device(&device);
+    }
+}
+
+/// Finds a mapped device by its UUID, by its name, or by its device ID, in that order.
+///
+/// The UUID and the name are ignored if they are empty.
+fn lookup_device(name: &str, uuid: &str, id: DeviceId) -> Result<Arc<MappedDevice>> {
+    let devices = MAPPED_DEVICES.lock();
+
+    let device = if !uuid.is_empty() {
+        devices.values().find(|device| device.uuid == uuid)
+    } else if !name.is_empty() {
+        devices.values().find(|device| device.name == name)
+    } else if id.major() == DM_MAJOR {
+        devices.get(&id.minor())
+    } else {
+        None
+    };
+    device
+        .cloned()
+        .ok_or_else(|| Error::with_message(Errno::ENXIO, "the mapped device does not exist"))
+}
+
+/// A mapped device.
+struct MappedDevice {
+    minor: u32,
+    name: String,
+    uuid: String,
+    state: SpinLock<DeviceState>,
+    /// The wait queue for the bios that wait for the device to be resumed.
+    resume_queue: WaitQueue,
+    /// The number of the opened files, the mounted file systems, and the tables of the device.
+    nr_openers: AtomicUsize,
+    weak_self: Weak<MappedDevice>,
+}
+
+struct DeviceState {
+    active: Option<Arc<Table>>,
+    inactive: Option<Arc<Table>>,
+    is_suspended: bool,
+}
+
+impl MappedDevice {
+    // The replaced tables are dropped after the lock is released, since dropping a table closes
+    // the underlying devices, which may sleep.
+
+    fn load_table(&self, table: Table) {
+        let old_table = self.state.lock().inactive.replace(Arc::new(table));
+        drop(old_table);
+    }
+
+    fn clear_table(&self) {
+        let old_table = self.state.lock().inactive.take();
+        drop(old_table);
+    }
+
+    /// Suspends the device, after which the new bios wait until the device is resumed.
+    ///
+    /// The bios that are being served are not waited for.
+    fn suspend(&self) {
+        self.state.lock().is_suspended = true;
+    }
+
+    /// Resumes the device, after which the inactive table (if any) becomes active.
+    fn resume(&self) {
+        let mut state = self.state.lock();
+        let old_table = match state.inactive.take() {
+            Some(table) => state.active.replace(table),
+            None => None,
+        };
+        state.is_suspended = false;
+        drop(state);
+        drop(old_table);
+
+        self.resume_queue.wake_all();
+    }
+
+    fn handle_bio(&self, bio: &SubmittedBio) -> BioStatus {
+        let table = self.resume_queue.wait_until(|| {
+            let state = self.state.lock();
+            (!state.is_suspended).then(|| state.active.clone())
+        });
+        let Some(table) = table else {
+            return BioStatus::IoError;
+        };
+
+        match table.handle_bio(bio) {
+            Ok(()) => BioStatus::Complete,
+            Err(err) if err.error() == Errno::EOPNOTSUPP => BioStatus::NotSupported,
+            Err(_) => BioStatus::IoError,
+        }
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.state
+            .lock()
+            .active
+            .as_ref()
+            .map_or(0, |table| table.nr_sectors())
+    }
+}
+
+impl Device for MappedDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::BlockDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(DM_MAJOR, self.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let device = self.weak_self.upgrade().unwrap();
+        Ok(Some(Arc::new(DmHandle::new(device))))
+    }
+}
+
+impl Pollable for MappedDevice {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for MappedDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the mapped device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the mapped device is not opened");
+    }
+}
+
+/// An opened `/dev/dm-N`, or a mapped device that is used by a mounted file system or a table.
+struct DmHandle {
+    device: Arc<MappedDevice>,
+}
+
+impl DmHandle {
+    fn new(device: Arc<MappedDevice>) -> Self {
+        device.nr_openers.fetch_add(1, Ordering::Relaxed);
+        Self { device }
+    }
+}
+
+impl Drop for DmHandle {
+    fn drop(&mut self) {
+        self.device.nr_openers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Debug for DmHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("DmHandle")
+            .field("name", &self.device.name)
+            .field("minor", &self.device.minor)
+            .finish()
+    }
+}
+
+impl BlockDevice for DmHandle {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        // The bio is served synchronously since the targets submit their own bios.
+        let status = self.device.handle_bio(&bio);
+        bio.complete(status);
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: MAX_NR_SEGMENTS_PER_BIO,
+            nr_sectors: self.device.nr_sectors(),
+        }
+    }
+}
+
+impl Pollable for DmHandle {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for DmHandle {
+    // TODO: Support reading and writing the mapped devices directly. `FileIo` does not know the
+    // file offset, so the devices can only be mounted now.
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the mapped device cannot be read directly");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the mapped device cannot be written directly"
+        );
+    }
+}
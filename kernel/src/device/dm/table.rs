@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{fmt::Display, ops::Range};
+
+use aster_block::{
+    bio::{BioStatus, BioType, SubmittedBio},
+    BlockDevice, SECTOR_SIZE,
+};
+use ostd::mm::VmIo;
+
+use super::{linear::LinearTarget, verity::VerityTarget};
+use crate::{
+    fs::{device::DeviceId, fs_resolver::FsPath, utils::InodeType},
+    prelude::*,
+};
+
+/// A target of the device mapper, which maps a range of the sectors of a mapped device.
+pub(super) trait Target: Send + Sync {
+    /// Reads the sectors starting from `sector`, which is relative to the start of the target.
+    fn read(&self, sector: usize, buf: &mut [u8]) -> Result<()>;
+
+    /// Writes the sectors starting from `sector`, which is relative to the start of the target.
+    fn write(&self, sector: usize, buf: &[u8]) -> Result<()>;
+
+    /// Flushes the volatile write caches of the underlying devices.
+    fn flush(&self) -> Result<()>;
+
+    /// Returns the status of the target, which is shown by `dmsetup status`.
+    fn status(&self) -> String;
+
+    /// Returns the parameters of the target in the table, which are shown by `dmsetup table`.
+    fn params(&self) -> String;
+}
+
+/// A table of a mapped device, which consists of the adjoining targets.
+pub(super) struct Table {
+    targets: Vec<TableTarget>,
+    is_read_only: bool,
+}
+
+/// A target in a table.
+pub(super) struct TableTarget {
+    pub(super) start: usize,
+    pub(super) len: usize,
+    pub(super) type_name: &'static str,
+    pub(super) target: Box<dyn Target>,
+}
+
+impl Table {
+    pub(super) fn new(is_read_only: bool) -> Self {
+        Self {
+            targets: Vec::new(),
+            is_read_only,
+        }
+    }
+
+    /// Adds a target of the type and the parameters, which maps `len` sectors from `start`.
+    ///
+    /// The target must start where the last target ends.
+    pub(super) fn add_target(
+        &mut self,
+        start: usize,
+        len: usize,
+        type_name: &str,
+        params: &str,
+    ) -> Result<()> {
+        if start != self.nr_sectors() {
+            return_errno_with_message!(Errno::EINVAL, "there is a gap in the table");
+        }
+        if len == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the target is empty");
+        }
+
+        let args: Vec<&str> = params.split_ascii_whitespace().collect();
+        let (type_name, target): (_, Box<dyn Target>) = match type_name {
+            "linear" => ("linear", Box::new(LinearTarget::new(&args)?)),
+            "verity" => {
+                if !self.is_read_only {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the verity target must be read-only"
+                    );
+                }
+                ("verity", Box::new(VerityTarget::new(len, &args)?))
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the target type is unknown"),
+        };
+
+        self.targets.push(TableTarget {
+            start,
+            len,
+            type_name,
+            target,
+        });
+        Ok(())
+    }
+
+    pub(super) fn targets(&self) -> &[TableTarget] {
+        &self.targets
+    }
+
+    pub(super) fn is_read_only(&self) -> bool {
+        self.is_read_only
+    }
+
+    pub(super) fn nr_sectors(&self) -> usize {
+        self.targets
+            .last()
+            .map_or(0, |target| target.start + target.len)
+    }
+
+    pub(super) fn handle_bio(&self, bio: &SubmittedBio) -> Result<()> {
+        let sid_range = bio.sid_range();
+        let start = sid_range.start.to_raw() as usize;
+        let nr_sectors = (sid_range.end.to_raw() - sid_range.start.to_raw()) as usize;
+
+        match bio.type_() {
+            BioType::Read | BioType::Write if start + nr_sectors > self.nr_sectors() => {
+                return_errno_with_message!(Errno::EIO, "the sectors are beyond the device");
+            }
+            BioType::Read => {
+                let mut buf = vec![0u8; nr_sectors * SECTOR_SIZE];
+                self.for_each_target(start, nr_sectors, |target, sector, range| {
+                    target.read(sector, &mut buf[range])
+                })?;
+
+                let mut offset = 0;
+                for segment in bio.segments() {
+                    let nbytes = segment.nbytes();
+                    segment.write_bytes(0, &buf[offset..offset + nbytes])?;
+                    offset += nbytes;
+                }
+            }
+            BioType::Write => {
+                if self.is_read_only {
+                    return_errno_with_message!(Errno::EROFS, "the mapped device is read-only");
+                }
+
+                let mut buf = vec![0u8; nr_sectors * SECTOR_SIZE];
+                let mut offset = 0;
+                for segment in bio.segments() {
+                    let nbytes = segment.nbytes();
+                    segment.read_bytes(0, &mut buf[offset..offset + nbytes])?;
+                    offset += nbytes;
+                }
+
+                self.for_each_target(start, nr_sectors, |target, sector, range| {
+                    target.write(sector, &buf[range])
+                })?;
+            }
+            BioType::Flush => {
+                for target in self.targets.iter() {
+                    target.target.flush()?;
+                }
+            }
+            BioType::Discard => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the discard is not supported");
+            }
+            BioType::WriteZeroes => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the write zeroes is not supported");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with each target that maps the sectors, the first sector relative to the
+    /// target, and the range of the sectors in bytes relative to the first sector.
+    fn for_each_target<F>(&self, start: usize, nr_sectors: usize, mut f: F) -> Result<()>
+    where
+        F: FnMut(&dyn Target, usize, Range<usize>) -> Result<()>,
+    {
+        let end = start + nr_sectors;
+
+        let first_index = self
+            .targets
+            .partition_point(|target| target.start + target.len <= start);
+        for target in self.targets[first_index..].iter() {
+            if target.start >= end {
+                break;
+            }
+
+            let map_start = start.max(target.start);
+            let map_end = end.min(target.start + target.len);
+            let range = (map_start - start) * SECTOR_SIZE..(map_end - start) * SECTOR_SIZE;
+            f(target.target.as_ref(), map_start - target.start, range)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An underlying device of a target.
+pub(super) struct TargetDevice {
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl TargetDevice {
+    /// Opens the device of a parameter, which is either `<major>:<minor>` or a path to the
+    /// device file.
+    pub(super) fn open(arg: &str) -> Result<Self> {
+        let id = if let Some((major, minor)) = arg.split_once(':') {
+            let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) else {
+                return_errno_with_message!(Errno::EINVAL, "the device number is invalid");
+            };
+            DeviceId::new(major, minor)
+        } else {
+            let fs_path = FsPath::try_from(arg)?;
+            let dentry = current_thread!()
+                .as_posix_thread()
+                .unwrap()
+                .fs()
+                .resolver()
+                .read()
+                .lookup(&fs_path)?;
+            let inode = dentry.inode();
+            if inode.type_() != InodeType::BlockDevice {
+                return_errno_with_message!(Errno::ENOTBLK, "the file is not a block device");
+            }
+            let Some(device) = inode.as_device() else {
+                return_errno_with_message!(Errno::ENXIO, "the block device does not exist");
+            };
+            device.id()
+        };
+
+        let device = crate::device::open_block_device(id)?;
+        Ok(Self { id, device })
+    }
+
+    pub(super) fn read(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.device.read_bytes(offset, buf)?;
+        Ok(())
+    }
+
+    pub(super) fn write(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.device.write_bytes(offset, buf)?;
+        Ok(())
+    }
+
+    pub(super) fn flush(&self) -> Result<()> {
+        match self.device.sync()? {
+            BioStatus::Complete => Ok(()),
+            _ => return_errno_with_message!(Errno::EIO, "the device cannot be flushed"),
+        }
+    }
+
+    /// Returns the size of the device in bytes.
+    pub(super) fn size(&self) -> usize {
+        self.device.metadata().nr_sectors * SECTOR_SIZE
+    }
+}
+
+impl Display for TargetDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}:{}", self.id.major(), self.id.minor())
+    }
+}
@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `verity` target, which verifies the blocks of a read-only data device against a hash tree
+//! on a hash device.
+//!
+//! The parameters are
+//! `<version> <data device> <hash device> <data block size> <hash block size>
+//! <number of data blocks> <hash start block> <algorithm> <root digest> <salt>
+//! [<number of optional parameters> <optional parameters>...]`.
+//!
+//! Only the version 1 format (i.e., the format created by `veritysetup format`) and the `sha256`
+//! algorithm are supported. The only supported optional parameter is `ignore_corruption`.
+//!
+//! The hash tree consists of levels of hash blocks, each of which contains the digests of the
+//! blocks in the level below it. The lowest level contains the digests of the data blocks, and
+//! the root digest is the digest of the only block in the highest level. The highest level is
+//! stored first on the hash device. The digest of a block is the digest of the salt followed by
+//! the block.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/device-mapper/verity.html>
+
+use core::{
+    fmt::Write,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use aster_block::SECTOR_SIZE;
+use lru::LruCache;
+
+use super::{
+    sha256::{Sha256, DIGEST_SIZE},
+    table::{Target, TargetDevice},
+};
+use crate::prelude::*;
+
+/// The number of the verified hash blocks that are cached.
+const HASH_CACHE_SIZE: usize = 64;
+
+pub(super) struct VerityTarget {
+    data_device: TargetDevice,
+    hash_device: TargetDevice,
+    data_block_size: usize,
+    hash_block_size: usize,
+    nr_data_blocks: usize,
+    hash_start: usize,
+    root_digest: [u8; DIGEST_SIZE],
+    salt: Vec<u8>,
+    ignore_corruption: bool,
+    /// The log2 of the number of the digests in a hash block.
+    hash_per_block_bits: u32,
+    /// The first hash block of each level, where the lowest level is level 0.
+    level_starts: Vec<usize>,
+    /// The verified hash blocks, which are indexed by the block numbers on the hash device.
+    hash_cache: Mutex<LruCache<usize, Arc<[u8]>>>,
+    is_corrupted: AtomicBool,
+}
+
+impl VerityTarget {
+    pub(super) fn new(len: usize, args: &[&str]) -> Result<Self> {
+        if args.len() < 10 {
+            return_errno_with_message!(Errno::EINVAL, "the verity target needs ten parameters");
+        }
+
+        if args[0] != "1" {
+            return_errno_with_message!(Errno::EINVAL, "the verity version is not supported");
+        }
+        let data_block_size = parse_block_size(args[3])?;
+        let hash_block_size = parse_block_size(args[4])?;
+        let nr_data_blocks: usize = parse_number(args[5])?;
+        let hash_start: usize = parse_number(args[6])?;
+        if args[7] != "sha256" {
+            return_errno_with_message!(Errno::EINVAL, "the hash algorithm is not supported");
+        }
+        let Ok(root_digest) = <[u8; DIGEST_SIZE]>::try_from(parse_hex(args[8])?) else {
+            return_errno_with_message!(Errno::EINVAL, "the root digest has a wrong size");
+        };
+        let salt = if args[9] == "-" {
+            Vec::new()
+        } else {
+            parse_hex(args[9])?
+        };
+
+        let mut ignore_corruption = false;
+        if let Some(nr_opt_args) = args.get(10) {
+            let nr_opt_args: usize = parse_number(nr_opt_args)?;
+            if args.len() != 11 + nr_opt_args {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the number of the optional parameters is wrong"
+                );
+            }
+            for opt_arg in &args[11..] {
+                match *opt_arg {
+                    "ignore_corruption" => ignore_corruption = true,
+                    _ => return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the optional parameter is not supported"
+                    ),
+                }
+            }
+        }
+
+        if nr_data_blocks
+            .checked_mul(data_block_size)
+            .is_none_or(|size| len * SECTOR_SIZE > size)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the target is larger than the data");
+        }
+
+        let hash_per_block_bits = (hash_block_size / DIGEST_SIZE).ilog2();
+        let (level_starts, hash_end) =
+            Self::layout(nr_data_blocks, hash_per_block_bits, hash_start);
+
+        let data_device = TargetDevice::open(args[1])?;
+        let hash_device = TargetDevice::open(args[2])?;
+        if data_device.size() / data_block_size < nr_data_blocks {
+            return_errno_with_message!(Errno::EINVAL, "the data device is too small");
+        }
+        if hash_device.size() / hash_block_size < hash_end {
+            return_errno_with_message!(Errno::EINVAL, "the hash device is too small");
+        }
+
+        Ok(Self {
+            data_device,
+            hash_device,
+            data_block_size,
+            hash_block_size,
+            nr_data_blocks,
+            hash_start,
+            root_digest,
+            salt,
+            ignore_corruption,
+            hash_per_block_bits,
+            level_starts,
+            hash_cache: Mutex::new(LruCache::new(NonZeroUsize::new(HASH_CACHE_SIZE).unwrap())),
+            is_corrupted: AtomicBool::new(false),
+        })
+    }
+
+    /// Computes the first hash block of each level, where the lowest level is level 0, and the
+    /// block after the last hash block.
+    fn layout(
+        nr_data_blocks: usize,
+        hash_per_block_bits: u32,
+        hash_start: usize,
+    ) -> (Vec<usize>, usize) {
+        // The number of the blocks in each level, from the lowest level.
+        let mut level_sizes = Vec::new();
+        let mut nr_blocks = nr_data_blocks;
+        while nr_blocks > 1 {
+            nr_blocks = nr_blocks.div_ceil(1 << hash_per_block_bits);
+            level_sizes.push(nr_blocks);
+        }
+
+        let mut level_starts = vec![0; level_sizes.len()];
+        let mut position = hash_start;
+        for (start, size) in level_starts.iter_mut().zip(level_sizes).rev() {
+            *start = position;
+            position += size;
+        }
+        (level_starts, position)
+    }
+
+    fn digest(&self, block: &[u8]) -> [u8; DIGEST_SIZE] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(block);
+        hasher.finalize()
+    }
+
+    /// Returns the hash block and the offset of the digest in it for a block in the level
+    /// below `level`.
+    fn hash_position(&self, data_block: usize, level: usize) -> (usize, usize) {
+        let position = data_block >> (level as u32 * self.hash_per_block_bits);
+        let block = self.level_starts[level] + (position >> self.hash_per_block_bits);
+        let offset = (position & ((1 << self.hash_per_block_bits) - 1)) * DIGEST_SIZE;
+        (block, offset)
+    }
+
+    /// Reads and verifies the data block, walking the hash tree from the root.
+    fn read_verified_block(&self, data_block: usize, buf: &mut [u8]) -> Result<()> {
+        let mut want = self.root_digest;
+
+        for level in (0..self.level_starts.len()).rev() {
+            let (block, offset) = self.hash_position(data_block, level);
+            let hash_block = self.read_hash_block(block, &want)?;
+            want.copy_from_slice(&hash_block[offset..offset + DIGEST_SIZE]);
+        }
+
+        self.data_device
+            .read(data_block * self.data_block_size, buf)?;
+        if self.digest(buf) != want {
+            self.handle_corruption("data", data_block)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the hash block and verifies it against the digest if it is not cached.
+    fn read_hash_block(&self, block: usize, want: &[u8; DIGEST_SIZE]) -> Result<Arc<[u8]>> {
+        if let Some(hash_block) = self.hash_cache.lock().get(&block) {
+            return Ok(hash_block.clone());
+        }
+
+        let mut hash_block = vec![0; self.hash_block_size];
+        self.hash_device
+            .read(block * self.hash_block_size, &mut hash_block)?;
+        if self.digest(&hash_block) != *want {
+            self.handle_corruption("hash", block)?;
+            // The corrupted block is not cached, so that it is reported again.
+            return Ok(hash_block.into());
+        }
+
+        let hash_block: Arc<[u8]> = hash_block.into();
+        self.hash_cache.lock().put(block, hash_block.clone());
+        Ok(hash_block)
+    }
+
+    fn handle_corruption(&self, type_name: &str, block: usize) -> Result<()> {
+        self.is_corrupted.store(true, Ordering::Relaxed);
+        warn!("dm-verity: the {} block {} is corrupted", type_name, block);
+
+        if self.ignore_corruption {
+            return Ok(());
+        }
+        return_errno_with_message!(Errno::EIO, "the block is corrupted");
+    }
+}
+
+impl Target for VerityTarget {
+    fn read(&self, sector: usize, buf: &mut [u8]) -> Result<()> {
+        let start = sector * SECTOR_SIZE;
+        let end = start + buf.len();
+
+        let mut block_buf = vec![0; self.data_block_size];
+        let mut data_block = start / self.data_block_size;
+        while data_block * self.data_block_size < end {
+            self.read_verified_block(data_block, &mut block_buf)?;
+
+            let block_start = data_block * self.data_block_size;
+            let copy_start = start.max(block_start);
+            let copy_end = end.min(block_start + self.data_block_size);
+            buf[copy_start - start..copy_end - start]
+                .copy_from_slice(&block_buf[copy_start - block_start..copy_end - block_start]);
+
+            data_block += 1;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, _sector: usize, _buf: &[u8]) -> Result<()> {
+        return_errno_with_message!(Errno::EROFS, "the verity target is read-only");
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn status(&self) -> String {
+        if self.is_corrupted.load(Ordering::Relaxed) {
+            "C".to_string()
+        } else {
+            "V".to_string()
+        }
+    }
+
+    fn params(&self) -> String {
+        let salt = if self.salt.is_empty() {
+            "-".to_string()
+        } else {
+            format_hex(&self.salt)
+        };
+        let mut params = format!(
+            "1 {} {} {} {} {} {} sha256 {} {}",
+            self.data_device,
+            self.hash_device,
+            self.data_block_size,
+            self.hash_block_size,
+            self.nr_data_blocks,
+            self.hash_start,
+            format_hex(&self.root_digest),
+            salt,
+        );
+        if self.ignore_corruption {
+            params.push_str(" 1 ignore_corruption");
+        }
+        params
+    }
+}
+
+fn parse_number(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not a number"))
+}
+
+fn parse_block_size(value: &str) -> Result<usize> {
+    let block_size = parse_number(value)?;
+    if !block_size.is_power_of_two() || !(SECTOR_SIZE..=PAGE_SIZE).contains(&block_size) {
+        return_errno_with_message!(Errno::EINVAL, "the block size is invalid");
+    }
+    Ok(block_size)
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>> {
+    if value.len() % 2 != 0 || !value.is_ascii() {
+        return_errno_with_message!(Errno::EINVAL, "the value is not a hex string");
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&value[i..i + 2], 16)
+                .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not a hex string"))
+        })
+        .collect()
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
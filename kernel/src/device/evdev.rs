@@ -0,0 +1,580 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The evdev interface of the input devices (`/dev/input/eventN`).
+//!
+//! Each opened file has its own queue of `struct input_event`s. The events that a device reports
+//! are grouped into packets, which end with `SYN_REPORT` and are delivered to the queues at once.
+//! If a queue is full, its events are dropped and `SYN_DROPPED` is reported instead, so that the
+//! userspace can resynchronize the device state with the ioctls.
+//!
+//! The keys of the keyboards are repeated by this layer, since the drivers only report the
+//! presses and the releases.
+//!
+//! Reference: <https://docs.kernel.org/input/input.html#evdev>
+
+use core::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+use aster_input::{
+    key::{Key, KeyStatus},
+    InputCapability, InputDevice, InputEvent, KEY_COUNT,
+};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+    syscall::ClockId,
+    time::{
+        clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
+        timer::{Timeout, Timer},
+        timeval_t, Clock,
+    },
+};
+
+/// The major device number of the input devices.
+pub(super) const INPUT_MAJOR: u32 = 13;
+/// The first minor device number of the evdev devices.
+const EVDEV_MINOR_BASE: u32 = 64;
+/// The maximum number of the evdev devices.
+const EVDEV_MINORS: u32 = 32;
+
+/// The version of the evdev interface, which is the same as `EV_VERSION` of Linux.
+const EV_VERSION: u32 = 0x010001;
+
+/// The maximum number of the pending events of an opened file.
+const EVENT_QUEUE_CAPACITY: usize = 256;
+
+/// The default delay and period of the key repeat, which are the same as Linux.
+const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(250);
+const DEFAULT_REPEAT_PERIOD: Duration = Duration::from_millis(33);
+
+// The event types.
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_REP: u16 = 0x14;
+
+// The codes of the `EV_SYN` events.
+const SYN_REPORT: u16 = 0;
+const SYN_DROPPED: u16 = 3;
+
+// The values of the `EV_KEY` events.
+const KEY_RELEASED: i32 = 0;
+const KEY_PRESSED: i32 = 1;
+const KEY_REPEATED: i32 = 2;
+
+/// The evdev devices, which are indexed by their minor device numbers minus
+/// [`EVDEV_MINOR_BASE`].
+static EVDEV_DEVICES: Mutex<Vec<Arc<EvdevDevice>>> = Mutex::new(Vec::new());
+
+pub(super) fn init() -> Result<()> {
+    for (name, device) in aster_input::all_devices() {
+        register(name, device)?;
+    }
+    Ok(())
+}
+
+/// Attaches the evdev interface to an input device and adds its device node.
+fn register(name: String, input_device: Arc<dyn InputDevice>) -> Result<()> {
+    let mut devices = EVDEV_DEVICES.lock();
+
+    let index = devices.len() as u32;
+    if index >= EVDEV_MINORS {
+        return_errno_with_message!(Errno::ENOSPC, "too many evdev devices");
+    }
+
+    let capability = input_device.capability();
+    let device = Arc::new_cyclic(|weak_device: &Weak<EvdevDevice>| {
+        let weak_device = weak_device.clone();
+        let repeat_timer = MonotonicClock::timer_manager().create_timer(move || {
+            if let Some(device) = weak_device.upgrade() {
+                device.repeat_key();
+            }
+        });
+
+        EvdevDevice {
+            minor: EVDEV_MINOR_BASE + index,
+            name,
+            // Only the keyboards repeat their keys.
+            can_repeat: capability.has_key(Key::A),
+            capability,
+            state: SpinLock::new(EvdevState {
+                packet: Vec::new(),
+                keys: [0; KEY_COUNT / 64],
+                repeated_key: None,
+                repeat_delay: DEFAULT_REPEAT_DELAY,
+                repeat_period: DEFAULT_REPEAT_PERIOD,
+            }),
+            clients: SpinLock::new(Vec::new()),
+            grabber: SpinLock::new(Weak::new()),
+            repeat_timer,
+        }
+    });
+
+    let callback_device = device.clone();
+    let callback: &'static _ = Box::leak(Box::new(move |event: InputEvent| {
+        callback_device.handle_event(event)
+    }));
+    input_device.register_callbacks(callback);
+
+    add_node(
+        Arc::new(EvdevDev {
+            device: device.clone(),
+        }),
+        &format!("input/event{}", index),
+        "input",
+    )?;
+    devices.push(device);
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let device = minor
+        .checked_sub(EVDEV_MINOR_BASE)
+        .and_then(|index| EVDEV_DEVICES.lock().get(index as usize).cloned());
+    let Some(device) = device else {
+        return_errno_with_message!(Errno::ENODEV, "the input device does not exist");
+    };
+    Ok(Arc::new(EvdevDev { device }))
+}
+
+/// An input device with the evdev interface.
+struct EvdevDevice {
+    minor: u32,
+    name: String,
+    capability: InputCapability,
+    can_repeat: bool,
+    state: SpinLock<EvdevState>,
+    clients: SpinLock<Vec<Weak<EvdevFile>>>,
+    /// The opened file that receives the events exclusively, which is set by `EVIOCGRAB`.
+    grabber: SpinLock<Weak<EvdevFile>>,
+    repeat_timer: Arc<Timer>,
+}
+
+struct EvdevState {
+    /// The events of the packet that is not finished by a `Sync` event yet.
+    packet: Vec<RawEvent>,
+    /// The bitmap of the pressed keys.
+    keys: [u64; KEY_COUNT / 64],
+    repeated_key: Option<Key>,
+    repeat_delay: Duration,
+    repeat_period: Duration,
+}
+
+/// An event without its timestamp.
+#[derive(Debug, Clone, Copy)]
+struct RawEvent {
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+impl RawEvent {
+    const fn new(type_: u16, code: u16, value: i32) -> Self {
+        Self { type_, code, value }
+    }
+}
+
+impl EvdevDevice {
+    /// Handles an event that the input device reports.
+    ///
+    /// This method may be called in the interrupt context.
+    fn handle_event(&self, event: InputEvent) {
+        let mut state = self.state.disable_irq().lock();
+
+        match event {
+            InputEvent::KeyBoard(key, status) => {
+                let code = key as usize;
+                let (bit, value) = (1 << (code % 64), &mut state.keys[code / 64]);
+                let pressed = status == KeyStatus::Pressed;
+                // Linux ignores the events that do not change the key state.
+                if (*value & bit != 0) == pressed {
+                    return;
+                }
+                if pressed {
+                    *value |= bit;
+                } else {
+                    *value &= !bit;
+                }
+
+                self.update_repeat(&mut state, key, pressed);
+                let value = if pressed { KEY_PRESSED } else { KEY_RELEASED };
+                state.packet.push(RawEvent::new(EV_KEY, key as u16, value));
+            }
+            InputEvent::Relative(axis, value) => {
+                if value != 0 {
+                    state.packet.push(RawEvent::new(EV_REL, axis as u16, value));
+                }
+            }
+            InputEvent::Sync => {
+                if state.packet.is_empty() {
+                    return;
+                }
+                state.packet.push(RawEvent::new(EV_SYN, SYN_REPORT, 0));
+                let packet = core::mem::take(&mut state.packet);
+                drop(state);
+                self.deliver(&packet);
+            }
+        }
+    }
+
+    fn update_repeat(&self, state: &mut EvdevState, key: Key, pressed: bool) {
+        if !self.can_repeat || key as u16 >= Key::BtnLeft as u16 {
+            return;
+        }
+
+        if pressed {
+            state.repeated_key = Some(key);
+            self.repeat_timer.set_interval(state.repeat_period);
+            self.repeat_timer
+                .set_timeout(Timeout::After(state.repeat_delay));
+        } else if state.repeated_key == Some(key) {
+            state.repeated_key = None;
+            self.repeat_timer.set_interval(Duration::ZERO);
+            self.repeat_timer.cancel();
+        }
+    }
+
+    /// Reports the repeat of the last pressed key, which is called by the repeat timer.
+    fn repeat_key(&self) {
+        let state = self.state.disable_irq().lock();
+        let Some(key) = state.repeated_key else {
+            // The key is released while the timer is firing, so stop the timer from being set
+            // again with the interval.
+            self.repeat_timer.set_interval(Duration::ZERO);
+            return;
+        };
+        drop(state);
+
+        self.deliver(&[
+            RawEvent::new(EV_KEY, key as u16, KEY_REPEATED),
+            RawEvent::new(EV_SYN, SYN_REPORT, 0),
+        ]);
+    }
+
+    /// Delivers a packet to the opened files.
+    fn deliver(&self, packet: &[RawEvent]) {
+        let grabber = self.grabber.disable_irq().lock().upgrade();
+        if let Some(grabber) = grabber {
+            grabber.push_packet(packet);
+            return;
+        }
+
+        let mut clients = self.clients.disable_irq().lock();
+        clients.retain(|client| match client.upgrade() {
+            Some(client) => {
+                client.push_packet(packet);
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Returns the bitmap of the supported event types.
+    fn event_type_bitmap(&self) -> u64 {
+        let mut bitmap = 1 << EV_SYN;
+        if self.capability.has_keys() {
+            bitmap |= 1 << EV_KEY;
+        }
+        if self.capability.has_rel_axes() {
+            bitmap |= 1 << EV_REL;
+        }
+        if self.can_repeat {
+            bitmap |= 1 << EV_REP;
+        }
+        bitmap
+    }
+}
+
+/// The device node of an evdev device.
+struct EvdevDev {
+    device: Arc<EvdevDevice>,
+}
+
+impl Device for EvdevDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(INPUT_MAJOR, self.device.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let file = Arc::new(EvdevFile {
+            device: self.device.clone(),
+            events: SpinLock::new(VecDeque::new()),
+            clock_id: AtomicI32::new(ClockId::CLOCK_REALTIME as i32),
+            pollee: Pollee::new(),
+        });
+        file.device
+            .clients
+            .disable_irq()
+            .lock()
+            .push(Arc::downgrade(&file));
+        Ok(Some(file))
+    }
+}
+
+impl Pollable for EvdevDev {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for EvdevDev {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the input device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the input device is not opened");
+    }
+}
+
+/// An opened `/dev/input/eventN`.
+struct EvdevFile {
+    device: Arc<EvdevDevice>,
+    events: SpinLock<VecDeque<c_input_event>>,
+    /// The clock of the event timestamps, which is set by `EVIOCSCLOCKID`.
+    clock_id: AtomicI32,
+    pollee: Pollee,
+}
+
+impl EvdevFile {
+    fn push_packet(&self, packet: &[RawEvent]) {
+        let now = match ClockId::try_from(self.clock_id.load(Ordering::Relaxed)) {
+            Ok(ClockId::CLOCK_MONOTONIC) => MonotonicClock::get().read_time(),
+            Ok(ClockId::CLOCK_BOOTTIME) => BootTimeClock::get().read_time(),
+            _ => RealTimeClock::get().read_time(),
+        };
+        let to_c_event = |event: &RawEvent| c_input_event {
+            time: timeval_t::from(now),
+            type_: event.type_,
+            code: event.code,
+            value: event.value,
+        };
+
+        let mut events = self.events.disable_irq().lock();
+        if events.len() + packet.len() > EVENT_QUEUE_CAPACITY {
+            events.clear();
+            events.push_back(to_c_event(&RawEvent::new(EV_SYN, SYN_DROPPED, 0)));
+        }
+        events.extend(packet.iter().map(to_c_event));
+        drop(events);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if self.events.disable_irq().lock().is_empty() {
+            IoEvents::empty()
+        } else {
+            IoEvents::IN
+        }
+    }
+
+    fn get_repeat(&self, arg: Vaddr) -> Result<i32> {
+        let state = self.device.state.disable_irq().lock();
+        let repeat = [
+            state.repeat_delay.as_millis() as u32,
+            state.repeat_period.as_millis() as u32,
+        ];
+        drop(state);
+
+        current_userspace!().write_val(arg, &repeat)?;
+        Ok(0)
+    }
+
+    fn set_repeat(&self, arg: Vaddr) -> Result<i32> {
+        let [delay, period] = current_userspace!().read_val::<[u32; 2]>(arg)?;
+        if !self.device.can_repeat {
+            return_errno_with_message!(Errno::EINVAL, "the device does not repeat the keys");
+        }
+
+        let mut state = self.device.state.disable_irq().lock();
+        state.repeat_delay = Duration::from_millis(delay as u64);
+        state.repeat_period = Duration::from_millis(period as u64);
+        Ok(0)
+    }
+
+    fn grab(&self, arg: usize) -> Result<i32> {
+        let mut grabber = self.device.grabber.disable_irq().lock();
+        let is_grabbed_by_self = core::ptr::eq(grabber.as_ptr(), self);
+
+        if arg != 0 {
+            if grabber.strong_count() > 0 {
+                return_errno_with_message!(Errno::EBUSY, "the input device is grabbed");
+            }
+            let clients = self.device.clients.disable_irq().lock();
+            let Some(this) = clients
+                .iter()
+                .find(|client| core::ptr::eq(client.as_ptr(), self))
+            else {
+                return_errno_with_message!(Errno::ENODEV, "the file is not opened");
+            };
+            *grabber = this.clone();
+        } else {
+            if !is_grabbed_by_self {
+                return_errno_with_message!(Errno::EINVAL, "the input device is not grabbed");
+            }
+            *grabber = Weak::new();
+        }
+        Ok(0)
+    }
+
+    fn set_clock_id(&self, arg: Vaddr) -> Result<i32> {
+        let clock_id = current_userspace!().read_val::<i32>(arg)?;
+        let clock_id = match ClockId::try_from(clock_id) {
+            Ok(
+                clock_id @ (ClockId::CLOCK_REALTIME
+                | ClockId::CLOCK_MONOTONIC
+                | ClockId::CLOCK_BOOTTIME),
+            ) => clock_id,
+            _ => return_errno_with_message!(Errno::EINVAL, "the clock is not supported"),
+        };
+
+        self.clock_id.store(clock_id as i32, Ordering::Relaxed);
+        Ok(0)
+    }
+
+    fn get_name(&self, arg: Vaddr, size: usize) -> Result<i32> {
+        let mut name = self.device.name.as_bytes().to_vec();
+        name.push(0);
+        let len = name.len().min(size);
+        current_userspace!().write_bytes(arg, &mut VmReader::from(&name[..len]))?;
+        Ok(len as i32)
+    }
+
+    /// Writes the bitmap to the userspace buffer of `size` bytes.
+    ///
+    /// Returns the number of the bytes that are written, which is the same as Linux.
+    fn write_bitmap(&self, arg: Vaddr, size: usize, bitmap: &[u64]) -> Result<i32> {
+        let bytes = bitmap
+            .iter()
+            .flat_map(|bits| bits.to_le_bytes())
+            .collect::<Vec<_>>();
+        let len = bytes.len().min(size);
+        current_userspace!().write_bytes(arg, &mut VmReader::from(&bytes[..len]))?;
+        Ok(len as i32)
+    }
+}
+
+impl Pollable for EvdevFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileIo for EvdevFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        if writer.avail() < size_of::<c_input_event>() {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for an event");
+        }
+
+        let mut len = 0;
+        while writer.avail() >= size_of::<c_input_event>() {
+            let Some(event) = self.events.disable_irq().lock().pop_front() else {
+                break;
+            };
+            if let Err(err) = writer.write_val(&event) {
+                if len == 0 {
+                    return Err(err.into());
+                }
+                break;
+            }
+            len += size_of::<c_input_event>();
+        }
+        self.pollee.invalidate();
+
+        if len == 0 {
+            return_errno_with_message!(Errno::EAGAIN, "there are no input events");
+        }
+        Ok(len)
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "injecting input events is not supported");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let no_bits = [0u64; 2];
+        match cmd {
+            IoctlCmd::EVIOCGVERSION => {
+                current_userspace!().write_val(arg, &EV_VERSION)?;
+                Ok(0)
+            }
+            IoctlCmd::EVIOCGID => {
+                current_userspace!().write_val(arg, &c_input_id::new_zeroed())?;
+                Ok(0)
+            }
+            IoctlCmd::EVIOCGREP => self.get_repeat(arg),
+            IoctlCmd::EVIOCSREP => self.set_repeat(arg),
+            IoctlCmd::EVIOCGRAB => self.grab(arg),
+            IoctlCmd::EVIOCSCLOCKID => self.set_clock_id(arg),
+            IoctlCmd::EVIOCGNAME => self.get_name(arg, cmd.size()),
+            IoctlCmd::EVIOCGPHYS | IoctlCmd::EVIOCGUNIQ => {
+                return_errno_with_message!(Errno::ENOENT, "the input device has no such string")
+            }
+            IoctlCmd::EVIOCGKEY => {
+                let keys = self.device.state.disable_irq().lock().keys;
+                self.write_bitmap(arg, cmd.size(), &keys)
+            }
+            IoctlCmd::EVIOCGBIT_EV => {
+                self.write_bitmap(arg, cmd.size(), &[self.device.event_type_bitmap()])
+            }
+            IoctlCmd::EVIOCGBIT_KEY => {
+                self.write_bitmap(arg, cmd.size(), self.device.capability.key_bitmap())
+            }
+            IoctlCmd::EVIOCGBIT_REL => {
+                self.write_bitmap(arg, cmd.size(), &[self.device.capability.rel_axis_bitmap()])
+            }
+            // The devices have no properties, LEDs, switches, or other kinds of events.
+            IoctlCmd::EVIOCGPROP
+            | IoctlCmd::EVIOCGLED
+            | IoctlCmd::EVIOCGSW
+            | IoctlCmd::EVIOCGBIT_ABS
+            | IoctlCmd::EVIOCGBIT_MSC
+            | IoctlCmd::EVIOCGBIT_SW
+            | IoctlCmd::EVIOCGBIT_LED
+            | IoctlCmd::EVIOCGBIT_SND
+            | IoctlCmd::EVIOCGBIT_FF => self.write_bitmap(arg, cmd.size(), &no_bits),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/input.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_input_event {
+    time: timeval_t,
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/input.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_input_id {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::*;
+use crate::{
+    events::IoEvents,
+    fs::{fuse::FuseDevFile, inode_handle::FileIo},
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The FUSE device.
+///
+/// Each opening of `/dev/fuse` creates a new connection, which is passed to `mount` with the
+/// file descriptor.
+pub struct Fuse;
+
+impl Device for Fuse {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(10, 229)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(FuseDevFile::new())))
+    }
+}
+
+impl Pollable for Fuse {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for Fuse {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the FUSE device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the FUSE device is not opened");
+    }
+}
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The GPIO character devices (`/dev/gpiochipN`), which give userspace access to the GPIO lines.
+//!
+//! Only the version 2 of the Linux UAPI is supported. Watching the changes of the line
+//! information is not supported yet.
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/gpio/chardev.html>
+
+mod request;
+
+use aster_gpio::{GpioDevice, GpioError, LineInfo, LINES_MAX};
+use ostd::task::Task;
+use request::{
+    c_gpio_v2_line_attribute, c_gpio_v2_line_request, LineRequestFile, LINE_ATTR_ID_DEBOUNCE,
+    LINE_NUM_ATTRS_MAX,
+};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        file_table::FdFlags,
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the GPIO devices.
+///
+/// Linux allocates the number dynamically, and this is the number that it usually gets.
+pub(super) const GPIO_MAJOR: u32 = 254;
+
+/// The default number of the pending events per requested line.
+const EVENTS_PER_LINE: usize = 16;
+
+pub(super) fn init() -> Result<()> {
+    for device in aster_gpio::all_devices() {
+        let name = device.name();
+        add_node(Arc::new(GpioDev { device }), &name, "gpio")?;
+    }
+    Ok(())
+}
+
+/// Returns the device of the GPIO chip index.
+pub(super) fn get_device(index: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = aster_gpio::get_device(index) else {
+        return_errno_with_message!(Errno::ENODEV, "the GPIO chip does not exist");
+    };
+    Ok(Arc::new(GpioDev { device }))
+}
+
+struct GpioDev {
+    device: Arc<GpioDevice>,
+}
+
+impl Device for GpioDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(GPIO_MAJOR, self.device.index())
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(GpioChipFile {
+            device: self.device.clone(),
+        })))
+    }
+}
+
+impl Pollable for GpioDev {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for GpioDev {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the GPIO chip is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the GPIO chip is not opened");
+    }
+}
+
+/// An opened `/dev/gpiochipN`.
+struct GpioChipFile {
+    device: Arc<GpioDevice>,
+}
+
+impl GpioChipFile {
+    fn chip_info(&self, arg: Vaddr) -> Result<i32> {
+        let mut info = c_gpiochip_info::new_zeroed();
+        copy_cstr(&mut info.name, &self.device.name());
+        copy_cstr(&mut info.label, self.device.chip().label());
+        info.lines = self.device.chip().ngpio();
+
+        current_userspace!().write_val(arg, &info)?;
+        Ok(0)
+    }
+
+    fn line_info(&self, arg: Vaddr) -> Result<i32> {
+        let info: c_gpio_v2_line_info = current_userspace!().read_val(arg)?;
+        if info.padding.iter().any(|padding| *padding != 0) {
+            return_errno_with_message!(Errno::EINVAL, "the padding is not zero");
+        }
+
+        let info = c_gpio_v2_line_info::from(self.device.line_info(info.offset)?);
+        current_userspace!().write_val(arg, &info)?;
+        Ok(0)
+    }
+
+    fn request_lines(&self, arg: Vaddr) -> Result<i32> {
+        let current_task = Task::current().unwrap();
+        let user_space = CurrentUserSpace::new(&current_task);
+        let mut line_request: c_gpio_v2_line_request = user_space.read_val(arg)?;
+
+        let num_lines = line_request.num_lines as usize;
+        if num_lines == 0 || num_lines > LINES_MAX {
+            return_errno_with_message!(Errno::EINVAL, "the number of GPIO lines is invalid");
+        }
+        if line_request.padding.iter().any(|padding| *padding != 0) {
+            return_errno_with_message!(Errno::EINVAL, "the padding is not zero");
+        }
+
+        let settings = line_request.config.to_settings(num_lines)?;
+        let lines = line_request.offsets[..num_lines]
+            .iter()
+            .copied()
+            .zip(settings)
+            .collect::<Vec<_>>();
+        let consumer = {
+            let len = line_request
+                .consumer
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(line_request.consumer.len());
+            String::from_utf8_lossy(&line_request.consumer[..len]).into_owned()
+        };
+        let event_capacity = match line_request.event_buffer_size as usize {
+            0 => num_lines * EVENTS_PER_LINE,
+            size => size.min(LINES_MAX * EVENTS_PER_LINE),
+        };
+
+        let request = self
+            .device
+            .request_lines(&consumer, &lines, event_capacity)?;
+        let file = LineRequestFile::new(request);
+
+        let thread_local = current_task.as_thread_local().unwrap();
+        let file_table = thread_local.borrow_file_table();
+        let mut file_table_locked = file_table.unwrap().write();
+        line_request.fd = file_table_locked.insert(file, FdFlags::CLOEXEC);
+        if let Err(err) = user_space.write_val(arg, &line_request) {
+            file_table_locked.close_file(line_request.fd).unwrap();
+            return Err(err);
+        }
+
+        Ok(0)
+    }
+}
+
+impl Pollable for GpioChipFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        // There are no events, since watching the line information is not supported.
+        IoEvents::empty() & mask
+    }
+}
+
+impl FileIo for GpioChipFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "watching the GPIO lines is not supported");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the GPIO chip cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::GPIO_GET_CHIPINFO_IOCTL => self.chip_info(arg),
+            IoctlCmd::GPIO_V2_GET_LINEINFO_IOCTL => self.line_info(arg),
+            IoctlCmd::GPIO_V2_GET_LINE_IOCTL => self.request_lines(arg),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+impl From<GpioError> for Error {
+    fn from(err: GpioError) -> Self {
+        match err {
+            GpioError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the GPIO operation is not supported")
+            }
+            GpioError::InvalidArgs => {
+                Error::with_message(Errno::EINVAL, "the GPIO operation is invalid")
+            }
+            GpioError::Busy => Error::with_message(Errno::EBUSY, "the GPIO line is requested"),
+            GpioError::PermissionDenied => Error::with_message(
+                Errno::EPERM,
+                "the GPIO operation is not allowed for the line configuration",
+            ),
+            GpioError::Io => Error::with_message(Errno::EIO, "the GPIO operation fails"),
+        }
+    }
+}
+
+/// Copies the string into the buffer as a C string, which is truncated if it is too long.
+fn copy_cstr(buf: &mut [u8], s: &str) {
+    let len = s.len().min(buf.len() - 1);
+    buf[..len].copy_from_slice(&s.as_bytes()[..len]);
+    buf[len..].fill(0);
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_gpiochip_info {
+    name: [u8; 32],
+    label: [u8; 32],
+    lines: u32,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_gpio_v2_line_info {
+    name: [u8; 32],
+    consumer: [u8; 32],
+    offset: u32,
+    num_attrs: u32,
+    flags: u64,
+    attrs: [c_gpio_v2_line_attribute; LINE_NUM_ATTRS_MAX],
+    padding: [u32; 4],
+}
+
+impl From<LineInfo> for c_gpio_v2_line_info {
+    fn from(info: LineInfo) -> Self {
+        let mut c_info = Self::new_zeroed();
+        copy_cstr(&mut c_info.name, &info.name);
+        copy_cstr(&mut c_info.consumer, &info.consumer);
+        c_info.offset = info.offset;
+        c_info.flags = info.flags.bits();
+
+        if !info.debounce.is_zero() {
+            c_info.attrs[0] = c_gpio_v2_line_attribute {
+                id: LINE_ATTR_ID_DEBOUNCE,
+                padding: 0,
+                value: info.debounce.as_micros() as u32 as u64,
+            };
+            c_info.num_attrs = 1;
+        }
+        c_info
+    }
+}
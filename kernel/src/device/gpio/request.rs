@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The file of requested GPIO lines, which is returned by the `GPIO_V2_GET_LINE_IOCTL` ioctl.
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_gpio::{LineEvent, LineFlags, LineRequest, LineSettings, LINES_MAX};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        utils::{InodeMode, IoctlCmd, Metadata, StatusFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+};
+
+/// The maximum number of the attributes in a line configuration.
+pub(super) const LINE_NUM_ATTRS_MAX: usize = 10;
+
+pub(super) struct LineRequestFile {
+    request: LineRequest,
+    pollee: Pollee,
+    is_nonblocking: AtomicBool,
+}
+
+impl LineRequestFile {
+    pub(super) fn new(request: LineRequest) -> Arc<Self> {
+        let pollee = Pollee::new();
+
+        let notified_pollee = pollee.clone();
+        request.set_event_notifier(Box::new(move || notified_pollee.notify(IoEvents::IN)));
+
+        Arc::new(Self {
+            request,
+            pollee,
+            is_nonblocking: AtomicBool::new(false),
+        })
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if self.request.has_events() {
+            IoEvents::IN
+        } else {
+            IoEvents::empty()
+        }
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut len = 0;
+        while writer.avail() >= size_of::<c_gpio_v2_line_event>() {
+            let Some(event) = self.request.pop_event() else {
+                break;
+            };
+            writer.write_val(&c_gpio_v2_line_event::from(event))?;
+            len += size_of::<c_gpio_v2_line_event>();
+        }
+        self.pollee.invalidate();
+
+        if len == 0 {
+            return_errno_with_message!(Errno::EAGAIN, "there are no GPIO line events");
+        }
+        Ok(len)
+    }
+
+    fn set_config(&self, arg: Vaddr) -> Result<i32> {
+        let config: c_gpio_v2_line_config = current_userspace!().read_val(arg)?;
+        let settings = config.to_settings(self.request.offsets().len())?;
+        self.request.reconfigure(&settings)?;
+        Ok(0)
+    }
+
+    fn get_values(&self, arg: Vaddr) -> Result<i32> {
+        let mut values: c_gpio_v2_line_values = current_userspace!().read_val(arg)?;
+        values.bits = self.request.get_values(values.mask)?;
+        current_userspace!().write_val(arg, &values)?;
+        Ok(0)
+    }
+
+    fn set_values(&self, arg: Vaddr) -> Result<i32> {
+        let values: c_gpio_v2_line_values = current_userspace!().read_val(arg)?;
+        self.request.set_values(values.bits, values.mask)?;
+        Ok(0)
+    }
+}
+
+impl Pollable for LineRequestFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileLike for LineRequestFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        if writer.avail() < size_of::<c_gpio_v2_line_event>() {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for an event");
+        }
+
+        if self.is_nonblocking.load(Ordering::Relaxed) {
+            self.try_read(writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+        }
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::GPIO_V2_LINE_SET_CONFIG_IOCTL => self.set_config(arg),
+            IoctlCmd::GPIO_V2_LINE_GET_VALUES_IOCTL => self.get_values(arg),
+            IoctlCmd::GPIO_V2_LINE_SET_VALUES_IOCTL => self.set_values(arg),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking.load(Ordering::Relaxed) {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `LineRequestFile` to it.
+        Metadata::new_file(
+            0,
+            InodeMode::from_bits_truncate(0o600),
+            aster_block::BLOCK_SIZE,
+        )
+    }
+}
+
+/// The IDs of the line attributes, which are the same as Linux's `enum gpio_v2_line_attr_id`.
+pub(super) const LINE_ATTR_ID_FLAGS: u32 = 1;
+pub(super) const LINE_ATTR_ID_OUTPUT_VALUES: u32 = 2;
+pub(super) const LINE_ATTR_ID_DEBOUNCE: u32 = 3;
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct c_gpio_v2_line_attribute {
+    pub(super) id: u32,
+    pub(super) padding: u32,
+    /// The flags, the output values, or the debounce period in microseconds, depending on the ID.
+    pub(super) value: u64,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_gpio_v2_line_config_attribute {
+    attr: c_gpio_v2_line_attribute,
+    /// The lines to which the attribute applies.
+    mask: u64,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct c_gpio_v2_line_config {
+    /// The flags of the lines to which no flags attribute applies.
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [c_gpio_v2_line_config_attribute; LINE_NUM_ATTRS_MAX],
+}
+
+impl c_gpio_v2_line_config {
+    /// Converts the configuration to the settings of the lines.
+    ///
+    /// For each line, the first attribute of each kind that applies to the line takes effect.
+    pub(super) fn to_settings(&self, num_lines: usize) -> Result<Vec<LineSettings>> {
+        let num_attrs = self.num_attrs as usize;
+        if num_attrs > LINE_NUM_ATTRS_MAX {
+            return_errno_with_message!(Errno::EINVAL, "too many GPIO line attributes");
+        }
+        if self.padding.iter().any(|padding| *padding != 0) {
+            return_errno_with_message!(Errno::EINVAL, "the padding is not zero");
+        }
+        let attrs = &self.attrs[..num_attrs];
+
+        let find_attr = |id: u32, line: usize| {
+            attrs
+                .iter()
+                .find(|attr| attr.attr.id == id && attr.mask & (1 << line) != 0)
+                .map(|attr| attr.attr.value)
+        };
+
+        (0..num_lines)
+            .map(|line| {
+                let flags = find_attr(LINE_ATTR_ID_FLAGS, line).unwrap_or(self.flags);
+                let Some(flags) = LineFlags::from_bits(flags) else {
+                    return_errno_with_message!(Errno::EINVAL, "the GPIO line flags are invalid");
+                };
+                let output_value = find_attr(LINE_ATTR_ID_OUTPUT_VALUES, line)
+                    .is_some_and(|values| values & (1 << line) != 0);
+                // The debounce period is a `u32` in the union.
+                let debounce = find_attr(LINE_ATTR_ID_DEBOUNCE, line)
+                    .map_or(Duration::ZERO, |period| {
+                        Duration::from_micros(period as u32 as u64)
+                    });
+
+                Ok(LineSettings {
+                    flags,
+                    output_value,
+                    debounce,
+                })
+            })
+            .collect()
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct c_gpio_v2_line_request {
+    pub(super) offsets: [u32; LINES_MAX],
+    pub(super) consumer: [u8; 32],
+    pub(super) config: c_gpio_v2_line_config,
+    pub(super) num_lines: u32,
+    pub(super) event_buffer_size: u32,
+    pub(super) padding: [u32; 5],
+    pub(super) fd: i32,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_gpio_v2_line_values {
+    bits: u64,
+    mask: u64,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_gpio_v2_line_event {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+impl From<LineEvent> for c_gpio_v2_line_event {
+    fn from(event: LineEvent) -> Self {
+        Self {
+            timestamp_ns: event.timestamp.as_nanos() as u64,
+            id: event.id as u32,
+            offset: event.offset,
+            seqno: event.seqno,
+            line_seqno: event.line_seqno,
+            padding: [0; 6],
+        }
+    }
+}
@@ -0,0 +1,510 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ports of the virtio consoles, which are the hypervisor consoles (`/dev/hvcN`) and the
+//! virtio ports (`/dev/vportNpM`).
+//!
+//! The console ports are bound to the `hvcN` terminals. Each terminal has its own line discipline
+//! and job control like a serial terminal, and its window size follows the size reported by the
+//! host, so the foreground process group gets `SIGWINCH` on resizes. The port that serves as the
+//! console of the kernel is `hvc0`, which is the same terminal as `/dev/console`.
+//!
+//! Like Linux, every port also has a `vportNpM` device (the port M of the console device N),
+//! which gives raw access to the channel between the host and the guest. The device can only be
+//! opened once at a time, and the host is told whether it is opened. Reading the device returns
+//! the end of file if the host side is not connected and there is no data. The device of a console
+//! port cannot be opened.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_virtio::device::console::{self as virtio_console, ConsolePort, ConsolePortListener};
+
+use super::tty::{
+    get_n_tty,
+    ioctl::{tty_ioctl, TtyOps},
+    line_discipline::{LineDiscipline, BUFFER_CAPACITY},
+    new_job_control_and_ldisc,
+    termio::WinSize,
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::{
+        signal::{PollHandle, Pollable, Pollee},
+        JobControl, Terminal,
+    },
+    thread::work_queue::{submit_work_func, WorkPriority},
+};
+
+/// The major device number of the hypervisor consoles, which is the same as Linux.
+pub(super) const HVC_MAJOR: u32 = 229;
+/// The maximum number of the hypervisor consoles, which is the same as Linux.
+const HVC_MINORS: u32 = 16;
+
+/// The major device number of the virtio ports.
+///
+/// Linux allocates the number dynamically, so a fixed number in the dynamic range is used.
+pub(super) const VPORT_MAJOR: u32 = 249;
+
+/// The port is throttled when the read buffer of a terminal has less room than this.
+const THROTTLE_ROOM: usize = 128;
+/// The port is unthrottled when the read buffer of a terminal has more room than this.
+const UNTHROTTLE_ROOM: usize = 1024;
+
+/// The maximum number of the received bytes that are buffered for a virtio port.
+///
+/// The port is throttled if the buffer cannot hold the bytes of another receive, which are at
+/// most a page.
+const VPORT_BUFFER_CAPACITY: usize = 16 * PAGE_SIZE;
+
+/// The hypervisor consoles, which are indexed by their minor device numbers.
+static HVC_TTYS: Mutex<Vec<HvcConsole>> = Mutex::new(Vec::new());
+
+/// The virtio ports, which are indexed by their minor device numbers.
+static VPORTS: Mutex<Vec<Arc<VirtioPort>>> = Mutex::new(Vec::new());
+
+pub(super) fn init() -> Result<()> {
+    virtio_console::register_port_callback(&on_port_changed);
+    for port in virtio_console::all_ports() {
+        bind_port(&port);
+    }
+    Ok(())
+}
+
+fn on_port_changed(port: &Arc<ConsolePort>) {
+    // The device nodes cannot be added in the interrupt context.
+    let port = port.clone();
+    submit_work_func(move || bind_port(&port), WorkPriority::Normal);
+}
+
+/// Binds the port to a virtio port device and, if it is a console port, a hypervisor console.
+///
+/// The port may be bound multiple times, e.g., when it becomes a console port after it is added.
+fn bind_port(port: &Arc<ConsolePort>) {
+    if let Err(err) = bind_vport(port) {
+        warn!(
+            "vport{}p{}: Failed to add the device: {:?}",
+            port.device_index(),
+            port.id(),
+            err
+        );
+    }
+
+    if port.is_console() || port.is_kernel_console() {
+        if let Err(err) = bind_hvc(port) {
+            warn!(
+                "vport{}p{}: Failed to add the console: {:?}",
+                port.device_index(),
+                port.id(),
+                err
+            );
+        }
+    }
+}
+
+fn bind_vport(port: &Arc<ConsolePort>) -> Result<()> {
+    let mut vports = VPORTS.lock();
+    if vports.iter().any(|vport| Arc::ptr_eq(&vport.port, port)) {
+        return Ok(());
+    }
+
+    let vport = VirtioPort::new(vports.len() as u32, port.clone());
+    add_node(
+        vport.clone(),
+        &format!("vport{}p{}", port.device_index(), port.id()),
+        "virtio-ports",
+    )?;
+    vports.push(vport);
+
+    Ok(())
+}
+
+fn bind_hvc(port: &Arc<ConsolePort>) -> Result<()> {
+    let mut hvc_ttys = HVC_TTYS.lock();
+    if hvc_ttys.iter().any(|hvc| Arc::ptr_eq(&hvc.port, port)) {
+        return Ok(());
+    }
+
+    let index = hvc_ttys.len() as u32;
+    if index >= HVC_MINORS {
+        return_errno_with_message!(Errno::ENOSPC, "too many hypervisor consoles");
+    }
+
+    let hvc = if port.is_kernel_console() {
+        // The input of the port is delivered to the console of the kernel, so the terminal of
+        // the console is reused. Only the window size is taken from the port.
+        let listener = Arc::new(KernelConsoleListener);
+        port.set_listener(Arc::downgrade(&listener) as Weak<dyn ConsolePortListener>);
+        if let Some((rows, cols)) = port.window_size() {
+            listener.on_resize(rows, cols);
+        }
+        HvcConsole {
+            port: port.clone(),
+            device: get_n_tty().clone(),
+            _listener: listener,
+        }
+    } else {
+        let tty = HvcTty::new(index, port.clone());
+        // The port may be throttled by its virtio port device.
+        port.unthrottle();
+        HvcConsole {
+            port: port.clone(),
+            device: tty.clone(),
+            _listener: tty,
+        }
+    };
+    add_node(hvc.device.clone(), &format!("hvc{}", index), "tty")?;
+    hvc_ttys.push(hvc);
+
+    Ok(())
+}
+
+/// Returns the hypervisor console of the minor device number.
+pub(super) fn get_hvc_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(hvc) = HVC_TTYS
+        .lock()
+        .get(minor as usize)
+        .map(|hvc| hvc.device.clone())
+    else {
+        return_errno_with_message!(Errno::ENODEV, "the hypervisor console does not exist");
+    };
+    Ok(hvc)
+}
+
+/// Returns the virtio port of the minor device number.
+pub(super) fn get_vport_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(vport) = VPORTS.lock().get(minor as usize).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the virtio port does not exist");
+    };
+    Ok(vport)
+}
+
+/// A hypervisor console.
+struct HvcConsole {
+    port: Arc<ConsolePort>,
+    device: Arc<dyn Device>,
+    /// The listener of the port, which is kept alive here.
+    _listener: Arc<dyn ConsolePortListener>,
+}
+
+/// The listener of the port that serves as the console of the kernel.
+struct KernelConsoleListener;
+
+impl ConsolePortListener for KernelConsoleListener {
+    fn on_receive(&self, _bytes: &[u8]) {
+        // The bytes are delivered to the console of the kernel via the console device.
+    }
+
+    fn on_resize(&self, rows: u16, cols: u16) {
+        get_n_tty()
+            .ldisc()
+            .set_window_size(WinSize::new(rows, cols));
+    }
+
+    fn on_host_connection(&self, _is_connected: bool) {}
+}
+
+/// A hypervisor console other than the console of the kernel.
+struct HvcTty {
+    index: u32,
+    port: Arc<ConsolePort>,
+    ldisc: Arc<LineDiscipline>,
+    job_control: Arc<JobControl>,
+    weak_self: Weak<Self>,
+}
+
+impl HvcTty {
+    fn new(index: u32, port: Arc<ConsolePort>) -> Arc<Self> {
+        let (job_control, ldisc) = new_job_control_and_ldisc();
+        if let Some((rows, cols)) = port.window_size() {
+            ldisc.set_window_size(WinSize::new(rows, cols));
+        }
+
+        let tty = Arc::new_cyclic(|weak_self| Self {
+            index,
+            port,
+            ldisc,
+            job_control,
+            weak_self: weak_self.clone(),
+        });
+        tty.port
+            .set_listener(tty.weak_self.clone() as Weak<dyn ConsolePortListener>);
+
+        tty
+    }
+}
+
+impl ConsolePortListener for HvcTty {
+    fn on_receive(&self, bytes: &[u8]) {
+        for ch in bytes {
+            self.ldisc.push_char(*ch, |content| {
+                self.port.write(content);
+            });
+        }
+
+        if BUFFER_CAPACITY - self.ldisc.buffer_len() < THROTTLE_ROOM {
+            self.port.throttle();
+        }
+    }
+
+    fn on_resize(&self, rows: u16, cols: u16) {
+        self.ldisc.set_window_size(WinSize::new(rows, cols));
+    }
+
+    fn on_host_connection(&self, _is_connected: bool) {
+        // Like Linux, the terminal is not hung up when the host side is closed.
+    }
+}
+
+impl Pollable for HvcTty {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.ldisc.poll(mask, poller)
+    }
+}
+
+impl FileIo for HvcTty {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut buf = vec![0; writer.avail()];
+        self.job_control.wait_until_in_foreground()?;
+        let read_len = self.ldisc.read(buf.as_mut_slice())?;
+        buf.truncate(read_len);
+        writer.write_fallible(&mut buf.as_slice().into())?;
+
+        if BUFFER_CAPACITY - self.ldisc.buffer_len() > UNTHROTTLE_ROOM {
+            self.port.unthrottle();
+        }
+        Ok(read_len)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let buf = reader.collect()?;
+        let output = self.ldisc.process_output(&buf);
+        self.port.write(&output);
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        tty_ioctl(self.weak_self.upgrade().unwrap(), cmd, arg, false)
+    }
+}
+
+impl TtyOps for HvcTty {
+    fn ldisc(&self) -> &LineDiscipline {
+        &self.ldisc
+    }
+
+    fn flush_input(&self) {
+        self.ldisc.drain_input();
+        self.port.unthrottle();
+    }
+}
+
+impl Terminal for HvcTty {
+    fn job_control(&self) -> &JobControl {
+        &self.job_control
+    }
+}
+
+impl Device for HvcTty {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(HVC_MAJOR, self.index)
+    }
+}
+
+/// A virtio port, which is the device of `/dev/vportNpM`.
+struct VirtioPort {
+    minor: u32,
+    port: Arc<ConsolePort>,
+    /// The received bytes.
+    buffer: SpinLock<VecDeque<u8>>,
+    is_opened: AtomicBool,
+    pollee: Pollee,
+}
+
+impl VirtioPort {
+    fn new(minor: u32, port: Arc<ConsolePort>) -> Arc<Self> {
+        // The host keeps the bytes until the device is opened. But the input of the console of
+        // the kernel must not be stopped.
+        if !port.is_kernel_console() {
+            port.throttle();
+        }
+
+        Arc::new(Self {
+            minor,
+            port,
+            buffer: SpinLock::new(VecDeque::new()),
+            is_opened: AtomicBool::new(false),
+            pollee: Pollee::new(),
+        })
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::empty();
+        if !self.buffer.disable_irq().lock().is_empty() {
+            events |= IoEvents::IN;
+        }
+        if self.port.is_host_connected() {
+            events |= IoEvents::OUT;
+        } else {
+            events |= IoEvents::HUP;
+        }
+        events
+    }
+
+    fn check_port(&self) -> Result<()> {
+        if self.port.is_removed() {
+            return_errno_with_message!(Errno::ENODEV, "the virtio port is removed");
+        }
+        Ok(())
+    }
+}
+
+impl ConsolePortListener for VirtioPort {
+    fn on_receive(&self, bytes: &[u8]) {
+        let mut buffer = self.buffer.disable_irq().lock();
+        buffer.extend(bytes);
+        if VPORT_BUFFER_CAPACITY.saturating_sub(buffer.len()) < PAGE_SIZE {
+            self.port.throttle();
+        }
+        drop(buffer);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    fn on_resize(&self, _rows: u16, _cols: u16) {}
+
+    fn on_host_connection(&self, is_connected: bool) {
+        if is_connected {
+            self.pollee.notify(IoEvents::OUT);
+        } else {
+            self.pollee.notify(IoEvents::HUP);
+        }
+    }
+}
+
+impl Device for VirtioPort {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(VPORT_MAJOR, self.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        self.check_port()?;
+        if self.port.is_console() || self.port.is_kernel_console() {
+            return_errno_with_message!(Errno::ENXIO, "the console port is used by the console");
+        }
+        if self.is_opened.swap(true, Ordering::Relaxed) {
+            return_errno_with_message!(Errno::EBUSY, "the virtio port is opened");
+        }
+
+        let vport = VPORTS.lock()[self.minor as usize].clone();
+        vport
+            .port
+            .set_listener(Arc::downgrade(&vport) as Weak<dyn ConsolePortListener>);
+        vport.port.set_guest_connected(true);
+        vport.port.unthrottle();
+        Ok(Some(Arc::new(VirtioPortFile { vport })))
+    }
+}
+
+impl Pollable for VirtioPort {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for VirtioPort {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the virtio port is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the virtio port is not opened");
+    }
+}
+
+/// An opened `/dev/vportNpM`.
+struct VirtioPortFile {
+    vport: Arc<VirtioPort>,
+}
+
+impl Pollable for VirtioPortFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.vport
+            .pollee
+            .poll_with(mask, poller, || self.vport.check_io_events())
+    }
+}
+
+impl FileIo for VirtioPortFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.wait_events(IoEvents::IN | IoEvents::HUP, None, || self.try_read(writer))
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.vport.check_port()?;
+
+        let mut buffer = self.vport.buffer.disable_irq().lock();
+        if buffer.is_empty() {
+            drop(buffer);
+            if !self.vport.port.is_host_connected() {
+                return Ok(0);
+            }
+            self.vport.pollee.invalidate();
+            return_errno_with_message!(Errno::EAGAIN, "the virtio port has no data");
+        }
+
+        let len = buffer.len().min(writer.avail());
+        let bytes = buffer.drain(..len).collect::<Vec<_>>();
+        let room = VPORT_BUFFER_CAPACITY.saturating_sub(buffer.len());
+        drop(buffer);
+
+        writer.write_fallible(&mut bytes.as_slice().into())?;
+        if room >= PAGE_SIZE {
+            self.vport.port.unthrottle();
+        }
+        Ok(len)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        self.vport.check_port()?;
+
+        // Like Linux, the writer waits until the host side is connected.
+        self.wait_events(IoEvents::OUT, None, || {
+            if self.vport.port.is_host_connected() {
+                Ok(())
+            } else {
+                self.vport.pollee.invalidate();
+                return_errno_with_message!(Errno::EAGAIN, "the host side is not connected")
+            }
+        })?;
+
+        let buf = reader.collect()?;
+        self.vport.port.write(&buf);
+        Ok(buf.len())
+    }
+}
+
+impl Drop for VirtioPortFile {
+    fn drop(&mut self) {
+        self.vport.port.set_guest_connected(false);
+        // The bytes that are not read are discarded, and the new bytes are kept by the host
+        // until the device is opened again.
+        if !self.vport.port.is_console() {
+            self.vport.port.throttle();
+        }
+        self.vport.buffer.disable_irq().lock().clear();
+        self.vport.is_opened.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I2C device interface (`/dev/i2c-N`), which gives userspace direct access to the I2C
+//! adapters.
+//!
+//! Reference: <https://docs.kernel.org/i2c/dev-interface.html>
+
+use aster_i2c::{
+    I2cAdapter, I2cError, I2cFunc, I2cMsg, I2cMsgFlags, SmbusData, SmbusProtocol, SmbusReadWrite,
+};
+use ostd::task::Task;
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the I2C devices, which is the same as Linux.
+pub(super) const I2C_MAJOR: u32 = 89;
+
+/// The maximum number of bytes in a message.
+const MAX_MSG_LEN: usize = 8192;
+
+/// The maximum number of messages in an `I2C_RDWR` ioctl.
+const RDWR_MAX_MSGS: usize = 42;
+
+pub(super) fn init() -> Result<()> {
+    for (bus_nr, adapter) in aster_i2c::all_adapters() {
+        add_node(
+            Arc::new(I2cDev::new(bus_nr, adapter)),
+            &format!("i2c-{}", bus_nr),
+            "i2c-dev",
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the device of the I2C bus number.
+pub(super) fn get_device(bus_nr: u32) -> Result<Arc<dyn Device>> {
+    let Some(adapter) = aster_i2c::get_adapter(bus_nr) else {
+        return_errno_with_message!(Errno::ENODEV, "the I2C adapter does not exist");
+    };
+    Ok(Arc::new(I2cDev::new(bus_nr, adapter)))
+}
+
+struct I2cDev {
+    bus_nr: u32,
+    adapter: Arc<dyn I2cAdapter>,
+}
+
+impl I2cDev {
+    fn new(bus_nr: u32, adapter: Arc<dyn I2cAdapter>) -> Self {
+        Self { bus_nr, adapter }
+    }
+
+    /// Creates a newly opened file, which uses the default client configuration.
+    fn new_file(&self) -> I2cDevFile {
+        I2cDevFile {
+            bus_nr: self.bus_nr,
+            adapter: self.adapter.clone(),
+            client: Mutex::new(ClientConfig::default()),
+        }
+    }
+}
+
+impl Device for I2cDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(I2C_MAJOR, self.bus_nr)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(self.new_file())))
+    }
+}
+
+// The client configuration belongs to the opened files. If the device is accessed without being
+// opened, it behaves like a newly opened file.
+
+impl Pollable for I2cDev {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.new_file().poll(mask, poller)
+    }
+}
+
+impl FileIo for I2cDev {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.new_file().read(writer)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        self.new_file().write(reader)
+    }
+}
+
+/// An opened `/dev/i2c-N`.
+///
+/// Each opened file has its own client configuration, which is used by `read`, `write`, and the
+/// `I2C_SMBUS` ioctl.
+struct I2cDevFile {
+    bus_nr: u32,
+    adapter: Arc<dyn I2cAdapter>,
+    client: Mutex<ClientConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ClientConfig {
+    addr: u16,
+    is_ten_bit: bool,
+}
+
+impl I2cDevFile {
+    fn set_addr(&self, addr: usize, is_forced: bool) -> Result<()> {
+        let mut client = self.client.lock();
+
+        let max_addr = if client.is_ten_bit { 0x3ff } else { 0x7f };
+        if addr > max_addr {
+            return_errno_with_message!(Errno::EINVAL, "the I2C address is invalid");
+        }
+        let addr = addr as u16;
+        if !is_forced && aster_i2c::is_addr_busy(self.bus_nr, addr) {
+            return_errno_with_message!(Errno::EBUSY, "the I2C address is used by a driver");
+        }
+
+        client.addr = addr;
+        Ok(())
+    }
+
+    fn set_ten_bit(&self, is_ten_bit: bool) -> Result<()> {
+        if is_ten_bit && !self.adapter.functionality().contains(I2cFunc::TEN_BIT_ADDR) {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "the I2C adapter does not support 10-bit addresses"
+            );
+        }
+        self.client.lock().is_ten_bit = is_ten_bit;
+        Ok(())
+    }
+
+    fn msg_flags(client: &ClientConfig) -> I2cMsgFlags {
+        if client.is_ten_bit {
+            I2cMsgFlags::TEN
+        } else {
+            I2cMsgFlags::empty()
+        }
+    }
+
+    fn rdwr(&self, arg: Vaddr) -> Result<i32> {
+        let current_task = Task::current().unwrap();
+        let user_space = CurrentUserSpace::new(&current_task);
+        let rdwr_data: c_i2c_rdwr_ioctl_data = user_space.read_val(arg)?;
+
+        let nmsgs = rdwr_data.nmsgs as usize;
+        if nmsgs > RDWR_MAX_MSGS {
+            return_errno_with_message!(Errno::EINVAL, "too many I2C messages");
+        }
+
+        let mut c_msgs = Vec::with_capacity(nmsgs);
+        let mut bufs = Vec::with_capacity(nmsgs);
+        for i in 0..nmsgs {
+            let c_msg: c_i2c_msg =
+                user_space.read_val(rdwr_data.msgs as Vaddr + i * size_of::<c_i2c_msg>())?;
+            let len = c_msg.len as usize;
+            if len > MAX_MSG_LEN {
+                return_errno_with_message!(Errno::EINVAL, "the I2C message is too long");
+            }
+            let flags = I2cMsgFlags::from_bits_truncate(c_msg.flags);
+
+            let mut buf = vec![0u8; len];
+            if !flags.contains(I2cMsgFlags::RD) || flags.contains(I2cMsgFlags::RECV_LEN) {
+                // For `RECV_LEN`, the first bytes tell the extra bytes to read (e.g., the PEC),
+                // so they are also copied in.
+                user_space
+                    .read_bytes(c_msg.buf as Vaddr, &mut VmWriter::from(buf.as_mut_slice()))?;
+            }
+
+            c_msgs.push(c_msg);
+            bufs.push((flags, buf));
+        }
+
+        let mut msgs = c_msgs
+            .iter()
+            .zip(bufs.iter_mut())
+            .map(|(c_msg, (flags, buf))| I2cMsg {
+                addr: c_msg.addr,
+                flags: *flags,
+                buf: buf.as_mut_slice(),
+            })
+            .collect::<Vec<_>>();
+        let nr_transferred = self.adapter.transfer(&mut msgs)?;
+        drop(msgs);
+
+        for (c_msg, (flags, buf)) in c_msgs.iter().zip(bufs.iter()) {
+            if flags.contains(I2cMsgFlags::RD) {
+                user_space.write_bytes(c_msg.buf as Vaddr, &mut VmReader::from(buf.as_slice()))?;
+            }
+        }
+
+        Ok(nr_transferred as i32)
+    }
+
+    fn smbus(&self, arg: Vaddr) -> Result<i32> {
+        let current_task = Task::current().unwrap();
+        let user_space = CurrentUserSpace::new(&current_task);
+        let smbus_data: c_i2c_smbus_ioctl_data = user_space.read_val(arg)?;
+
+        let read_write = SmbusReadWrite::try_from(smbus_data.read_write)?;
+        let protocol = SmbusProtocol::try_from(smbus_data.size)?;
+        let data_addr = smbus_data.data as Vaddr;
+
+        // The quick command and the byte write carry no data, so the data pointer is ignored.
+        let has_data = !(protocol == SmbusProtocol::Quick
+            || (protocol == SmbusProtocol::Byte && read_write == SmbusReadWrite::Write));
+        if has_data && data_addr == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the SMBus data pointer is null");
+        }
+
+        // The data is read from userspace if it is written to the device, or if the length of
+        // the block to read is given by userspace.
+        let mut data = SmbusData::new();
+        if has_data
+            && (read_write == SmbusReadWrite::Write
+                || protocol == SmbusProtocol::ProcCall
+                || protocol == SmbusProtocol::I2cBlockData)
+        {
+            data = user_space.read_val(data_addr)?;
+        }
+
+        let addr = self.client.lock().addr;
+        self.adapter
+            .smbus_xfer(addr, read_write, smbus_data.command, protocol, &mut data)?;
+
+        if has_data && (read_write == SmbusReadWrite::Read || protocol == SmbusProtocol::ProcCall) {
+            let len = match protocol {
+                SmbusProtocol::Byte | SmbusProtocol::ByteData => 1,
+                SmbusProtocol::WordData | SmbusProtocol::ProcCall => 2,
+                _ => size_of::<SmbusData>(),
+            };
+            user_space.write_bytes(data_addr, &mut VmReader::from(&data.as_bytes()[..len]))?;
+        }
+
+        Ok(0)
+    }
+}
+
+impl Pollable for I2cDevFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for I2cDevFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut buf = vec![0u8; writer.avail().min(MAX_MSG_LEN)];
+        let client = *self.client.lock();
+        let mut msgs = [I2cMsg {
+            addr: client.addr,
+            flags: Self::msg_flags(&client) | I2cMsgFlags::RD,
+            buf: buf.as_mut_slice(),
+        }];
+        self.adapter.transfer(&mut msgs)?;
+
+        writer.write_fallible(&mut buf.as_slice().into())?;
+        Ok(buf.len())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let mut buf = vec![0u8; reader.remain().min(MAX_MSG_LEN)];
+        reader.read_fallible(&mut buf.as_mut_slice().into())?;
+
+        let client = *self.client.lock();
+        let mut msgs = [I2cMsg {
+            addr: client.addr,
+            flags: Self::msg_flags(&client),
+            buf: buf.as_mut_slice(),
+        }];
+        self.adapter.transfer(&mut msgs)?;
+
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::I2C_SLAVE => self.set_addr(arg, false).map(|_| 0),
+            IoctlCmd::I2C_SLAVE_FORCE => self.set_addr(arg, true).map(|_| 0),
+            IoctlCmd::I2C_TENBIT => self.set_ten_bit(arg != 0).map(|_| 0),
+            IoctlCmd::I2C_FUNCS => {
+                let funcs = self.adapter.functionality().bits() as u64;
+                current_userspace!().write_val(arg, &funcs)?;
+                Ok(0)
+            }
+            IoctlCmd::I2C_RDWR => self.rdwr(arg),
+            IoctlCmd::I2C_SMBUS => self.smbus(arg),
+            IoctlCmd::I2C_PEC => {
+                if arg != 0 && !self.adapter.functionality().contains(I2cFunc::SMBUS_PEC) {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the I2C adapter does not support packet error checking"
+                    );
+                }
+                Ok(0)
+            }
+            // The adapters retry and time out on their own.
+            IoctlCmd::I2C_RETRIES | IoctlCmd::I2C_TIMEOUT => Ok(0),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+impl From<I2cError> for Error {
+    fn from(err: I2cError) -> Self {
+        match err {
+            I2cError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the I2C transfer is not supported")
+            }
+            I2cError::InvalidArgs => {
+                Error::with_message(Errno::EINVAL, "the I2C transfer is invalid")
+            }
+            I2cError::NoDevice => {
+                Error::with_message(Errno::ENXIO, "no I2C device acknowledges the address")
+            }
+            I2cError::Busy => Error::with_message(Errno::EBUSY, "the I2C adapter is busy"),
+            I2cError::ArbitrationLost => {
+                Error::with_message(Errno::EAGAIN, "the I2C bus arbitration is lost")
+            }
+            I2cError::Protocol => {
+                Error::with_message(Errno::EPROTO, "the I2C device violates the protocol")
+            }
+            I2cError::Timeout => {
+                Error::with_message(Errno::ETIMEDOUT, "the I2C transfer is timed out")
+            }
+            I2cError::Io => Error::with_message(Errno::EIO, "the I2C transfer fails"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/i2c-dev.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_i2c_rdwr_ioctl_data {
+    msgs: u64,
+    nmsgs: u32,
+    _pad: u32,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/i2c.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_i2c_msg {
+    addr: u16,
+    flags: u16,
+    len: u16,
+    _pad: u16,
+    buf: u64,
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/i2c-dev.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_i2c_smbus_ioctl_data {
+    read_write: u8,
+    command: u8,
+    _pad: u16,
+    size: u32,
+    data: u64,
+}
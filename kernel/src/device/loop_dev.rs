@@ -0,0 +1,656 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The loop devices (`/dev/loopN`), which expose regular files as block devices.
+//!
+//! A loop device is bound to a file with the `LOOP_CONFIGURE` or `LOOP_SET_FD` ioctl. After that,
+//! the device can be mounted like a disk (e.g., to mount a disk image or a squashfs image), and
+//! the sectors of the device are read from and written to the file.
+//!
+//! The loop devices are managed with the ioctls on `/dev/loop-control`.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man4/loop.4.html>
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_block::{
+    bio::{BioEnqueueError, BioSegment, BioStatus, BioType, SubmittedBio},
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, delete_node, Device, DeviceId, DeviceType},
+        file_handle::FileLike,
+        file_table::FileDesc,
+        inode_handle::FileIo,
+        utils::{FallocMode, Inode, InodeType, IoctlCmd},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the loop devices, which is the same as Linux.
+pub(super) const LOOP_MAJOR: u32 = 7;
+
+/// The minor device number of `/dev/loop-control`, whose major device number is 10 (misc).
+pub(super) const LOOP_CTRL_MINOR: u32 = 237;
+
+/// The number of loop devices that are added at boot, which is the default of Linux.
+const NR_INITIAL_DEVICES: u32 = 8;
+
+/// The maximum number of segments in a bio.
+///
+/// The bios are served synchronously, so the limit only bounds the memory used by a bio.
+const MAX_NR_SEGMENTS_PER_BIO: usize = 128;
+
+const LO_NAME_SIZE: usize = 64;
+const LO_KEY_SIZE: usize = 32;
+
+/// The loop devices, which are indexed by their minor device numbers.
+static LOOP_DEVICES: Mutex<BTreeMap<u32, Arc<LoopDevice>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init() -> Result<()> {
+    for index in 0..NR_INITIAL_DEVICES {
+        add_device(Some(index))?;
+    }
+    add_node(Arc::new(LoopControl), "loop-control", "misc")?;
+    Ok(())
+}
+
+/// Returns the loop device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = LOOP_DEVICES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the loop device does not exist");
+    };
+    Ok(device)
+}
+
+/// Opens the loop device of the minor device number as a block device.
+///
+/// The loop device is kept open until the returned block device is dropped, so that it is
+/// detached only after it is no longer used (e.g., the file system on it is unmounted).
+pub(super) fn open_block_device(minor: u32) -> Result<Arc<dyn BlockDevice>> {
+    let Some(device) = LOOP_DEVICES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENXIO, "the loop device does not exist");
+    };
+    if device.backing.read().is_none() {
+        return_errno_with_message!(Errno::ENXIO, "the loop device is not bound to a file");
+    }
+    Ok(Arc::new(LoopHandle::new(device)))
+}
+
+/// Adds a loop device with the index, or with the smallest unused index if it is `None`.
+fn add_device(index: Option<u32>) -> Result<Arc<LoopDevice>> {
+    let mut devices = LOOP_DEVICES.lock();
+
+    let index = match index {
+        Some(index) if devices.contains_key(&index) => {
+            return_errno_with_message!(Errno::EEXIST, "the loop device already exists");
+        }
+        Some(index) => index,
+        None => (0..).find(|index| !devices.contains_key(index)).unwrap(),
+    };
+
+    let device = Arc::new_cyclic(|weak_self| LoopDevice {
+        index,
+        backing: RwMutex::new(None),
+        nr_openers: AtomicUsize::new(0),
+        weak_self: weak_self.clone(),
+    });
+    add_node(device.clone(), &format!("loop{}", index), "block")?;
+
+    devices.insert(index, device.clone());
+    Ok(device)
+}
+
+/// Removes the loop device with the index.
+fn remove_device(index: u32) -> Result<()> {
+    let mut devices = LOOP_DEVICES.lock();
+
+    let Some(device) = devices.get(&index) else {
+        return_errno_with_message!(Errno::ENODEV, "the loop device does not exist");
+    };
+    if device.backing.read().is_some() || device.nr_openers.load(Ordering::Relaxed) > 0 {
+        return_errno_with_message!(Errno::EBUSY, "the loop device is in use");
+    }
+
+    delete_node(&format!("loop{}", index), "block")?;
+    devices.remove(&index);
+    Ok(())
+}
+
+/// Returns a loop device that is not bound to a file, which is added if there is none.
+fn get_free_device() -> Result<Arc<LoopDevice>> {
+    let free_device = LOOP_DEVICES
+        .lock()
+        .values()
+        .find(|device| device.backing.read().is_none())
+        .cloned();
+    match free_device {
+        Some(device) => Ok(device),
+        None => add_device(None),
+    }
+}
+
+/// Returns the file of the file descriptor in the current thread.
+fn get_file(fd: FileDesc) -> Result<Arc<dyn FileLike>> {
+    let current = current_thread!();
+    let file_table = current.as_posix_thread().unwrap().file_table().lock();
+    let file_table = file_table
+        .as_ref()
+        .ok_or_else(|| Error::with_message(Errno::EBADF, "the file table is released"))?;
+    let file = file_table.read().get_file(fd)?.clone();
+    Ok(file)
+}
+
+/// A loop device.
+struct LoopDevice {
+    index: u32,
+    backing: RwMutex<Option<Backing>>,
+    /// The number of the opened files and the mounted file systems of the device.
+    nr_openers: AtomicUsize,
+    weak_self: Weak<LoopDevice>,
+}
+
+/// The file that a loop device is bound to.
+struct Backing {
+    // The file is kept open as long as the device is bound to it.
+    _file: Arc<dyn FileLike>,
+    inode: Arc<dyn Inode>,
+    offset: u64,
+    size_limit: u64,
+    flags: LoopFlags,
+    file_name: [u8; LO_NAME_SIZE],
+    nr_sectors: usize,
+}
+
+bitflags! {
+    /// The flags of a loop device (i.e., `LO_FLAGS_*` in Linux).
+    struct LoopFlags: u32 {
+        /// The device is read-only.
+        const READ_ONLY = 1 << 0;
+        /// The device is unbound from the file when it is closed for the last time.
+        const AUTOCLEAR = 1 << 2;
+        /// The partitions on the device are scanned.
+        const PARTSCAN  = 1 << 3;
+        /// The file is accessed with direct I/O.
+        const DIRECT_IO = 1 << 4;
+    }
+}
+
+impl LoopFlags {
+    /// The flags that can be set with `LOOP_SET_STATUS64`.
+    const SET_STATUS_SETTABLE: Self = Self::AUTOCLEAR.union(Self::PARTSCAN);
+    /// The flags that can be cleared with `LOOP_SET_STATUS64`.
+    const SET_STATUS_CLEARABLE: Self = Self::AUTOCLEAR;
+    /// The flags that can be set with `LOOP_CONFIGURE`.
+    const CONFIGURE_SETTABLE: Self = Self::READ_ONLY
+        .union(Self::AUTOCLEAR)
+        .union(Self::PARTSCAN)
+        .union(Self::DIRECT_IO);
+}
+
+impl LoopDevice {
+    /// Binds the device to the file of the file descriptor.
+    fn configure(&self, fd: FileDesc, info: &c_loop_info64) -> Result<()> {
+        let file = get_file(fd)?;
+        let inode = file.as_inode_or_err()?.dentry().inode().clone();
+        if inode.type_() != InodeType::File {
+            return_errno_with_message!(Errno::EINVAL, "the file is not a regular file");
+        }
+
+        let mut flags =
+            LoopFlags::from_bits_truncate(info.lo_flags) & LoopFlags::CONFIGURE_SETTABLE;
+        if !file.access_mode().is_writable() {
+            flags |= LoopFlags::READ_ONLY;
+        }
+
+        let mut backing = self.backing.write();
+        if backing.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the loop device is already bound");
+        }
+
+        let mut new_backing = Backing {
+            _file: file,
+            inode,
+            offset: 0,
+            size_limit: 0,
+            flags: LoopFlags::empty(),
+            file_name: [0; LO_NAME_SIZE],
+            nr_sectors: 0,
+        };
+        new_backing.set_status(info)?;
+        new_backing.flags = flags;
+        // Like Linux, the buffered I/O is used if the file does not support direct I/O.
+        if flags.contains(LoopFlags::DIRECT_IO) && !new_backing.supports_direct_io() {
+            new_backing.flags.remove(LoopFlags::DIRECT_IO);
+        }
+
+        *backing = Some(new_backing);
+        Ok(())
+    }
+
+    /// Unbinds the device from its file.
+    ///
+    /// If the device is still open elsewhere, it is unbound when it is closed for the last time.
+    fn clear(&self) -> Result<()> {
+        let mut backing = self.backing.write();
+        let Some(bound_backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        if self.nr_openers.load(Ordering::Relaxed) > 1 {
+            bound_backing.flags |= LoopFlags::AUTOCLEAR;
+        } else {
+            *backing = None;
+        }
+        Ok(())
+    }
+
+    fn set_status(&self, info: &c_loop_info64) -> Result<()> {
+        let mut backing = self.backing.write();
+        let Some(backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        backing.set_status(info)?;
+
+        let mut flags =
+            LoopFlags::from_bits_truncate(info.lo_flags) & LoopFlags::SET_STATUS_SETTABLE;
+        flags |= backing.flags - LoopFlags::SET_STATUS_SETTABLE;
+        flags |= backing.flags - LoopFlags::SET_STATUS_CLEARABLE;
+        backing.flags = flags;
+        Ok(())
+    }
+
+    fn get_status(&self) -> Result<c_loop_info64> {
+        let backing = self.backing.read();
+        let Some(backing) = backing.as_ref() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        let metadata = backing.inode.metadata();
+        let mut info = c_loop_info64::new_zeroed();
+        info.lo_device = metadata.dev;
+        info.lo_inode = metadata.ino;
+        info.lo_rdevice = metadata.rdev;
+        info.lo_offset = backing.offset;
+        info.lo_sizelimit = backing.size_limit;
+        info.lo_number = self.index;
+        info.lo_flags = backing.flags.bits();
+        info.lo_file_name = backing.file_name;
+        Ok(info)
+    }
+
+    fn set_capacity(&self) -> Result<()> {
+        let mut backing = self.backing.write();
+        let Some(backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        backing.update_capacity();
+        Ok(())
+    }
+
+    fn set_direct_io(&self, is_enabled: bool) -> Result<()> {
+        let mut backing = self.backing.write();
+        let Some(backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+
+        if is_enabled && !backing.supports_direct_io() {
+            return_errno_with_message!(Errno::EINVAL, "the file does not support direct I/O");
+        }
+        backing.flags.set(LoopFlags::DIRECT_IO, is_enabled);
+        Ok(())
+    }
+
+    fn handle_bio(&self, bio: &SubmittedBio) -> BioStatus {
+        let backing = self.backing.read();
+        let Some(backing) = backing.as_ref() else {
+            return BioStatus::IoError;
+        };
+
+        let sid_range = bio.sid_range();
+        if bio.type_() != BioType::Flush && sid_range.end.to_raw() > backing.nr_sectors as u64 {
+            return BioStatus::IoError;
+        }
+        let is_read_only = backing.flags.contains(LoopFlags::READ_ONLY);
+        let offset = backing.offset as usize + sid_range.start.to_offset();
+
+        let result = match bio.type_() {
+            BioType::Read => backing.read(offset, bio.segments()),
+            BioType::Write if is_read_only => return BioStatus::IoError,
+            BioType::Write => backing.write(offset, bio.segments()),
+            BioType::Flush => backing.inode.sync_data(),
+            BioType::Discard if is_read_only => return BioStatus::IoError,
+            BioType::Discard => backing.inode.fallocate(
+                FallocMode::PunchHoleKeepSize,
+                offset,
+                sid_range.end.to_offset() - sid_range.start.to_offset(),
+            ),
+            BioType::WriteZeroes if is_read_only => return BioStatus::IoError,
+            BioType::WriteZeroes => backing.inode.fallocate(
+                FallocMode::ZeroRangeKeepSize,
+                offset,
+                sid_range.end.to_offset() - sid_range.start.to_offset(),
+            ),
+        };
+        match result {
+            Ok(()) => BioStatus::Complete,
+            Err(err) if err.error() == Errno::EOPNOTSUPP => BioStatus::NotSupported,
+            Err(err) if err.error() == Errno::ENOSPC => BioStatus::NoSpace,
+            Err(_) => BioStatus::IoError,
+        }
+    }
+}
+
+impl Backing {
+    fn set_status(&mut self, info: &c_loop_info64) -> Result<()> {
+        // The encryption has been removed from Linux.
+        if info.lo_encrypt_type != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the encryption is not supported");
+        }
+
+        self.offset = info.lo_offset;
+        self.size_limit = info.lo_sizelimit;
+        self.file_name = info.lo_file_name;
+        self.file_name[LO_NAME_SIZE - 1] = 0;
+        self.update_capacity();
+        Ok(())
+    }
+
+    /// Updates the number of sectors from the size of the file.
+    fn update_capacity(&mut self) {
+        let mut size = (self.inode.size() as u64).saturating_sub(self.offset);
+        if self.size_limit != 0 {
+            size = size.min(self.size_limit);
+        }
+        self.nr_sectors = size as usize / SECTOR_SIZE;
+    }
+
+    fn supports_direct_io(&self) -> bool {
+        let mut buf = [0u8; 0];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        self.inode.read_direct_at(0, &mut writer).is_ok()
+    }
+
+    fn read(&self, mut offset: usize, segments: &[BioSegment]) -> Result<()> {
+        for segment in segments {
+            let mut writer = segment.writer()?.to_fallible();
+            while writer.has_avail() {
+                let read_len = if self.flags.contains(LoopFlags::DIRECT_IO) {
+                    self.inode.read_direct_at(offset, &mut writer)?
+                } else {
+                    self.inode.read_at(offset, &mut writer)?
+                };
+                if read_len == 0 {
+                    // The sectors beyond the end of the file are read as zeros.
+                    let len = writer.avail();
+                    writer.fill_zeros(len).map_err(|(err, _)| err)?;
+                    offset += len;
+                    break;
+                }
+                offset += read_len;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, mut offset: usize, segments: &[BioSegment]) -> Result<()> {
+        for segment in segments {
+            let mut reader = segment.reader()?.to_fallible();
+            while reader.has_remain() {
+                let write_len = if self.flags.contains(LoopFlags::DIRECT_IO) {
+                    self.inode.write_direct_at(offset, &mut reader)?
+                } else {
+                    self.inode.write_at(offset, &mut reader)?
+                };
+                if write_len == 0 {
+                    return_errno_with_message!(Errno::EIO, "the file cannot be written");
+                }
+                offset += write_len;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Device for LoopDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::BlockDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(LOOP_MAJOR, self.index)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let device = self.weak_self.upgrade().unwrap();
+        Ok(Some(Arc::new(LoopHandle::new(device))))
+    }
+}
+
+impl Pollable for LoopDevice {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for LoopDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop device is not opened");
+    }
+}
+
+/// An opened `/dev/loopN`, or a loop device that is used by a mounted file system.
+///
+/// The device is unbound from its file when the last handle is dropped if
+/// [`LoopFlags::AUTOCLEAR`] is set.
+struct LoopHandle {
+    device: Arc<LoopDevice>,
+}
+
+impl LoopHandle {
+    fn new(device: Arc<LoopDevice>) -> Self {
+        device.nr_openers.fetch_add(1, Ordering::Relaxed);
+        Self { device }
+    }
+}
+
+impl Drop for LoopHandle {
+    fn drop(&mut self) {
+        let mut backing = self.device.backing.write();
+        if self.device.nr_openers.fetch_sub(1, Ordering::Relaxed) == 1
+            && backing
+                .as_ref()
+                .is_some_and(|backing| backing.flags.contains(LoopFlags::AUTOCLEAR))
+        {
+            *backing = None;
+        }
+    }
+}
+
+impl Debug for LoopHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("LoopHandle")
+            .field("index", &self.device.index)
+            .finish()
+    }
+}
+
+impl BlockDevice for LoopHandle {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        // The bio is served synchronously since the file system does the I/O on its own.
+        let status = self.device.handle_bio(&bio);
+        bio.complete(status);
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        let nr_sectors = self
+            .device
+            .backing
+            .read()
+            .as_ref()
+            .map_or(0, |backing| backing.nr_sectors);
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: MAX_NR_SEGMENTS_PER_BIO,
+            nr_sectors,
+        }
+    }
+
+    // The backing file returns `EOPNOTSUPP` if it cannot deallocate or zero the range.
+    fn max_discard_sectors(&self) -> usize {
+        usize::MAX
+    }
+
+    fn max_write_zeroes_sectors(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl Pollable for LoopHandle {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopHandle {
+    // TODO: Support reading and writing the loop devices directly. `FileIo` does not know the
+    // file offset, so only the ioctls are supported now.
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop device cannot be read directly");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop device cannot be written directly");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::LOOP_SET_FD => {
+                let info = c_loop_info64::new_zeroed();
+                self.device.configure(arg as FileDesc, &info)?;
+            }
+            IoctlCmd::LOOP_CONFIGURE => {
+                let config: c_loop_config = current_userspace!().read_val(arg)?;
+                // Only the 512-byte logical blocks are supported, but the sectors of other
+                // block sizes are still addressable.
+                let block_size = config.block_size as usize;
+                if block_size != 0
+                    && (!block_size.is_power_of_two()
+                        || !(SECTOR_SIZE..=PAGE_SIZE).contains(&block_size))
+                {
+                    return_errno_with_message!(Errno::EINVAL, "the block size is invalid");
+                }
+                self.device.configure(config.fd as FileDesc, &config.info)?;
+            }
+            IoctlCmd::LOOP_CLR_FD => self.device.clear()?,
+            IoctlCmd::LOOP_SET_STATUS64 => {
+                let info: c_loop_info64 = current_userspace!().read_val(arg)?;
+                self.device.set_status(&info)?;
+            }
+            IoctlCmd::LOOP_GET_STATUS64 => {
+                let info = self.device.get_status()?;
+                current_userspace!().write_val(arg, &info)?;
+            }
+            IoctlCmd::LOOP_SET_CAPACITY => self.device.set_capacity()?,
+            IoctlCmd::LOOP_SET_DIRECT_IO => self.device.set_direct_io(arg != 0)?,
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+        Ok(0)
+    }
+}
+
+/// The loop control device (`/dev/loop-control`), which adds and removes the loop devices.
+pub(super) struct LoopControl;
+
+impl Device for LoopControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, LOOP_CTRL_MINOR)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(LoopControl)))
+    }
+}
+
+impl Pollable for LoopControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopControl {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop control device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the loop control device cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            // A negative index means any unused index.
+            IoctlCmd::LOOP_CTL_ADD => {
+                let device = add_device(u32::try_from(arg as i32).ok())?;
+                Ok(device.index as i32)
+            }
+            IoctlCmd::LOOP_CTL_REMOVE => {
+                let index = u32::try_from(arg as i32).map_err(|_| {
+                    Error::with_message(Errno::ENODEV, "the loop device does not exist")
+                })?;
+                remove_device(index)?;
+                Ok(0)
+            }
+            IoctlCmd::LOOP_CTL_GET_FREE => {
+                let device = get_free_device()?;
+                Ok(device.index as i32)
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/loop.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_loop_info64 {
+    lo_device: u64,
+    lo_inode: u64,
+    lo_rdevice: u64,
+    lo_offset: u64,
+    lo_sizelimit: u64,
+    lo_number: u32,
+    lo_encrypt_type: u32,
+    lo_encrypt_key_size: u32,
+    lo_flags: u32,
+    lo_file_name: [u8; LO_NAME_SIZE],
+    lo_crypt_name: [u8; LO_NAME_SIZE],
+    lo_encrypt_key: [u8; LO_KEY_SIZE],
+    lo_init: [u64; 2],
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/loop.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_loop_config {
+    fd: u32,
+    block_size: u32,
+    info: c_loop_info64,
+    _reserved: [u64; 8],
+}
@@ -1,19 +1,38 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod disk;
+mod dm;
+mod evdev;
+mod fuse;
+mod gpio;
+mod hvc;
+mod i2c_dev;
+mod loop_dev;
 mod null;
+pub mod ptp;
 mod pty;
 mod random;
+mod rtc;
+mod serial;
 mod shm;
+mod sound;
+mod spidev;
 pub mod tty;
+mod tun;
 mod urandom;
+mod watchdog;
+mod whiteout;
 mod zero;
 
 #[cfg(all(target_arch = "x86_64", feature = "cvm_guest"))]
 mod tdxguest;
 
+use aster_block::BlockDevice;
+pub use disk::scan_partitions;
 pub use pty::{new_pty_pair, PtyMaster, PtySlave};
 pub use random::Random;
 pub use urandom::Urandom;
+pub use whiteout::Whiteout;
 
 use self::tty::get_n_tty;
 use crate::{
@@ -24,24 +43,41 @@ use crate::{
 /// Init the device node in fs, must be called after mounting rootfs.
 pub fn init() -> Result<()> {
     let null = Arc::new(null::Null);
-    add_node(null, "null")?;
+    add_node(null, "null", "mem")?;
     let zero = Arc::new(zero::Zero);
-    add_node(zero, "zero")?;
+    add_node(zero, "zero", "mem")?;
     tty::init();
     let console = get_n_tty().clone();
-    add_node(console, "console")?;
+    add_node(console, "console", "tty")?;
     let tty = Arc::new(tty::TtyDevice);
-    add_node(tty, "tty")?;
+    add_node(tty, "tty", "tty")?;
     #[cfg(target_arch = "x86_64")]
     ostd::if_tdx_enabled!({
-        add_node(Arc::new(tdxguest::TdxGuest), "tdx_guest")?;
+        add_node(Arc::new(tdxguest::TdxGuest), "tdx_guest", "misc")?;
     });
     let random = Arc::new(random::Random);
-    add_node(random, "random")?;
+    add_node(random, "random", "mem")?;
     let urandom = Arc::new(urandom::Urandom);
-    add_node(urandom, "urandom")?;
+    add_node(urandom, "urandom", "mem")?;
+    let fuse = Arc::new(fuse::Fuse);
+    add_node(fuse, "fuse", "misc")?;
+    let tun = Arc::new(tun::Tun);
+    add_node(tun, "net/tun", "misc")?;
     pty::init()?;
     shm::init()?;
+    i2c_dev::init()?;
+    gpio::init()?;
+    spidev::init()?;
+    serial::init()?;
+    hvc::init()?;
+    ptp::init()?;
+    rtc::init()?;
+    watchdog::init()?;
+    sound::init()?;
+    loop_dev::init()?;
+    dm::init()?;
+    disk::init()?;
+    evdev::init()?;
     Ok(())
 }
 
@@ -51,7 +87,7 @@ pub fn init() -> Result<()> {
 // allocate device IDs either statically or dynamically.
 pub fn get_device(dev: usize) -> Result<Arc<dyn Device>> {
     if dev == 0 {
-        return_errno_with_message!(Errno::EPERM, "whiteout device")
+        return Ok(Arc::new(Whiteout));
     }
 
     let devid = DeviceId::from(dev as u64);
@@ -64,6 +100,38 @@ pub fn get_device(dev: usize) -> Result<Arc<dyn Device>> {
         (5, 0) => Ok(Arc::new(tty::TtyDevice)),
         (1, 8) => Ok(Arc::new(random::Random)),
         (1, 9) => Ok(Arc::new(urandom::Urandom)),
+        (10, 229) => Ok(Arc::new(fuse::Fuse)),
+        (10, tun::TUN_MINOR) => Ok(Arc::new(tun::Tun)),
+        (10, loop_dev::LOOP_CTRL_MINOR) => Ok(Arc::new(loop_dev::LoopControl)),
+        (10, dm::DM_CTRL_MINOR) => Ok(Arc::new(dm::DmControl)),
+        (10, watchdog::WATCHDOG_MISC_MINOR) => watchdog::get_misc_device(),
+        (i2c_dev::I2C_MAJOR, bus_nr) => i2c_dev::get_device(bus_nr),
+        (gpio::GPIO_MAJOR, index) => gpio::get_device(index),
+        (spidev::SPIDEV_MAJOR, minor) => spidev::get_device(minor),
+        (serial::SERIAL_MAJOR, minor) => serial::get_device(minor),
+        (hvc::HVC_MAJOR, minor) => hvc::get_hvc_device(minor),
+        (hvc::VPORT_MAJOR, minor) => hvc::get_vport_device(minor),
+        (ptp::PTP_MAJOR, minor) => ptp::get_device(minor),
+        (rtc::RTC_MAJOR, minor) => rtc::get_device(minor),
+        (watchdog::WATCHDOG_MAJOR, minor) => watchdog::get_device(minor),
+        (sound::SOUND_MAJOR, minor) => sound::get_device(minor),
+        (loop_dev::LOOP_MAJOR, minor) => loop_dev::get_device(minor),
+        (dm::DM_MAJOR, minor) => dm::get_device(minor),
+        (evdev::INPUT_MAJOR, minor) => evdev::get_device(minor),
+        (aster_block::BLOCK_MAJOR, minor) => disk::get_device(minor),
         _ => return_errno_with_message!(Errno::EINVAL, "unsupported device"),
     }
 }
+
+/// Opens the block device of the device ID, e.g., to mount a file system on it.
+///
+/// The block devices that are registered by the drivers (e.g., the virtio block devices and
+/// their partitions) use the major device number of the block layer.
+pub fn open_block_device(id: DeviceId) -> Result<Arc<dyn BlockDevice>> {
+    match id.major() {
+        loop_dev::LOOP_MAJOR => loop_dev::open_block_device(id.minor()),
+        dm::DM_MAJOR => dm::open_block_device(id.minor()),
+        aster_block::BLOCK_MAJOR => disk::open_block_device(id.minor()),
+        _ => return_errno_with_message!(Errno::ENXIO, "the block device does not exist"),
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PHC of KVM guests (i.e., `ptp_kvm`), which reads the wall-clock time of the host.
+//!
+//! The guest clock also drifts away from the host clock, e.g., it falls behind by the downtime of
+//! a live migration. The host does not notify the guest, so the offset between them is checked
+//! periodically, and the real time is slewed if the offset is too large. It is stepped only if it
+//! falls far behind, so it never jumps backwards. The finer corrections are left to the user space,
+//! which can synchronize with this PHC.
+
+use core::{mem::ManuallyDrop, time::Duration};
+
+use ostd::arch::{
+    kvm::{clock_pairing, KvmClockPairing},
+    read_tsc, tsc_freq,
+};
+
+use super::{CrossTimestamp, PtpClock};
+use crate::{
+    prelude::*,
+    time::{
+        clocks::{MonotonicClock, MonotonicRawClock, RealTimeClock},
+        timer::Timeout,
+        Clock,
+    },
+};
+
+/// The interval to check the offset between the guest clock and the host clock.
+const DRIFT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// The maximum offset between the guest clock and the host clock before the real time is slewed.
+const MAX_DRIFT: Duration = Duration::from_millis(1);
+/// The minimum offset by which the real time falls behind the host clock before it is stepped.
+///
+/// Slewing the real time by this offset takes more than four minutes.
+const MIN_STEP: Duration = Duration::from_millis(128);
+
+struct KvmPtpClock;
+
+/// Returns the PHC if the host supports it.
+pub(super) fn probe() -> Option<Arc<dyn PtpClock>> {
+    clock_pairing()?;
+
+    let drift_timer = ManuallyDrop::new(MonotonicClock::timer_manager().create_timer(|| {
+        if let Err(err) = correct_drift() {
+            warn!("failed to correct the drift of the clock: {:?}", err);
+        }
+    }));
+    drift_timer.set_interval(DRIFT_CHECK_INTERVAL);
+    drift_timer.set_timeout(Timeout::After(DRIFT_CHECK_INTERVAL));
+
+    Some(Arc::new(KvmPtpClock))
+}
+
+impl PtpClock for KvmPtpClock {
+    fn read_time(&self) -> Result<Duration> {
+        let pairing = read_clock_pairing()?;
+        let tsc = read_tsc();
+        Ok(host_time(&pairing) + tsc_to_duration(tsc.saturating_sub(pairing.tsc)))
+    }
+
+    fn read_cross_timestamp(&self) -> Option<Result<CrossTimestamp>> {
+        Some(read_clock_pairing().map(|pairing| {
+            // The system times at the paired TSC are derived from the current ones, which are read
+            // with the TSC as close as possible.
+            let sys_realtime = RealTimeClock::get().read_time();
+            let sys_monoraw = MonotonicRawClock::get().read_time();
+            let elapsed = tsc_to_duration(read_tsc().saturating_sub(pairing.tsc));
+
+            CrossTimestamp {
+                device: host_time(&pairing),
+                sys_realtime: sys_realtime.saturating_sub(elapsed),
+                sys_monoraw: sys_monoraw.saturating_sub(elapsed),
+            }
+        }))
+    }
+}
+
+/// Slews or steps the real time to the host time if they differ too much.
+fn correct_drift() -> Result<()> {
+    let timestamp = KvmPtpClock.read_cross_timestamp().unwrap()?;
+
+    let is_behind = timestamp.device > timestamp.sys_realtime;
+    let drift = if is_behind {
+        timestamp.device - timestamp.sys_realtime
+    } else {
+        timestamp.sys_realtime - timestamp.device
+    };
+    if drift <= MAX_DRIFT {
+        return Ok(());
+    }
+
+    let realtime_clock = RealTimeClock::get();
+    if is_behind && drift >= MIN_STEP {
+        let new_time = realtime_clock.read_time() + drift;
+        realtime_clock.set_time(new_time);
+        info!("stepped the real time by {:?} to match the host", drift);
+        return Ok(());
+    }
+
+    // The drift is measured with the ongoing slew applied, so the new slew replaces it.
+    let nanos = drift.as_nanos().min(i64::MAX as u128) as i64;
+    realtime_clock.slew_time(if is_behind { nanos } else { -nanos });
+    debug!(
+        "slewing the real time {} by {:?} to match the host",
+        if is_behind { "forwards" } else { "backwards" },
+        drift
+    );
+
+    Ok(())
+}
+
+fn read_clock_pairing() -> Result<KvmClockPairing> {
+    clock_pairing().ok_or_else(|| Error::with_message(Errno::EIO, "failed to read the host clock"))
+}
+
+fn host_time(pairing: &KvmClockPairing) -> Duration {
+    Duration::new(pairing.sec as u64, pairing.nsec as u32)
+}
+
+fn tsc_to_duration(cycles: u64) -> Duration {
+    Duration::from_nanos((cycles as u128 * 1_000_000_000 / tsc_freq() as u128) as u64)
+}
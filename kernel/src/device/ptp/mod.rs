@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PTP hardware clocks (PHCs), which are exposed as `/dev/ptpN`.
+//!
+//! The user space (e.g., chrony) compares the system time with a PHC through the ioctls or reads
+//! the PHC as a dynamic POSIX clock, whose clock ID is derived from the file descriptor.
+//!
+//! Reference: <https://docs.kernel.org/driver-api/ptp.html>
+
+use core::time::Duration;
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        file_handle::FileLike,
+        inode_handle::{FileIo, InodeHandle},
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    time::{
+        clocks::{MonotonicClock, MonotonicRawClock, RealTimeClock},
+        Clock,
+    },
+};
+
+#[cfg(target_arch = "x86_64")]
+mod kvm;
+
+/// The major device number of the PHCs.
+///
+/// Linux allocates the number dynamically, so a fixed number in the dynamic range is used.
+pub(super) const PTP_MAJOR: u32 = 248;
+
+/// The maximum number of the samples in a `PTP_SYS_OFFSET*` ioctl, which is the same as Linux.
+const PTP_MAX_SAMPLES: u32 = 25;
+
+/// A PTP hardware clock.
+pub trait PtpClock: Send + Sync {
+    /// Reads the time of the clock.
+    fn read_time(&self) -> Result<Duration>;
+
+    /// Reads the time of the clock with the system times at the same instant.
+    ///
+    /// Returns `None` if the clock does not support cross timestamping.
+    fn read_cross_timestamp(&self) -> Option<Result<CrossTimestamp>> {
+        None
+    }
+}
+
+/// The time of a PHC and the system times at the same instant.
+#[derive(Debug, Clone, Copy)]
+pub struct CrossTimestamp {
+    pub device: Duration,
+    pub sys_realtime: Duration,
+    pub sys_monoraw: Duration,
+}
+
+/// The PHCs, which are indexed by their minor device numbers.
+static PTP_CLOCKS: Mutex<Vec<Arc<dyn PtpClock>>> = Mutex::new(Vec::new());
+
+pub(super) fn init() -> Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(clock) = kvm::probe() {
+        register(clock)?;
+    }
+
+    Ok(())
+}
+
+/// Registers a PHC and adds its device node.
+fn register(clock: Arc<dyn PtpClock>) -> Result<()> {
+    let mut clocks = PTP_CLOCKS.lock();
+
+    let minor = clocks.len() as u32;
+    add_node(
+        Arc::new(PtpDevice {
+            minor,
+            clock: clock.clone(),
+        }),
+        &format!("ptp{}", minor),
+        "ptp",
+    )?;
+
+    clocks.push(clock);
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(clock) = PTP_CLOCKS.lock().get(minor as usize).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the PTP clock does not exist");
+    };
+    Ok(Arc::new(PtpDevice { minor, clock }))
+}
+
+/// Returns the PHC of an opened `/dev/ptpN`, which is used as a dynamic POSIX clock.
+pub fn get_clock(file: &dyn FileLike) -> Option<Arc<dyn PtpClock>> {
+    let inode_handle = file.downcast_ref::<InodeHandle>()?;
+    let device = inode_handle.dentry().inode().as_device()?;
+    let id = device.id();
+    if id.major() != PTP_MAJOR {
+        return None;
+    }
+    PTP_CLOCKS.lock().get(id.minor() as usize).cloned()
+}
+
+struct PtpDevice {
+    minor: u32,
+    clock: Arc<dyn PtpClock>,
+}
+
+impl Device for PtpDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(PTP_MAJOR, self.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(PtpFile {
+            clock: self.clock.clone(),
+        })))
+    }
+}
+
+impl Pollable for PtpDevice {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for PtpDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the PTP clock is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the PTP clock is not opened");
+    }
+}
+
+/// An opened `/dev/ptpN`.
+struct PtpFile {
+    clock: Arc<dyn PtpClock>,
+}
+
+impl PtpFile {
+    fn get_caps(&self, arg: usize) -> Result<()> {
+        let caps = c_ptp_clock_caps {
+            cross_timestamping: self.clock.read_cross_timestamp().is_some() as i32,
+            ..Default::default()
+        };
+        current_userspace!().write_val(arg, &caps)
+    }
+
+    fn sys_offset(&self, arg: usize) -> Result<()> {
+        let mut offset: c_ptp_sys_offset = current_userspace!().read_val(arg)?;
+        let n_samples = offset.n_samples;
+        if n_samples > PTP_MAX_SAMPLES {
+            return_errno_with_message!(Errno::EINVAL, "there are too many samples");
+        }
+
+        // The PHC time of each sample is placed between two system times.
+        let realtime_clock = RealTimeClock::get();
+        for i in 0..n_samples as usize {
+            offset.ts[2 * i] = realtime_clock.read_time().into();
+            offset.ts[2 * i + 1] = self.clock.read_time()?.into();
+        }
+        offset.ts[2 * n_samples as usize] = realtime_clock.read_time().into();
+
+        current_userspace!().write_val(arg, &offset)
+    }
+
+    fn sys_offset_extended(&self, arg: usize) -> Result<()> {
+        let mut offset: c_ptp_sys_offset_extended = current_userspace!().read_val(arg)?;
+        if offset.n_samples > PTP_MAX_SAMPLES {
+            return_errno_with_message!(Errno::EINVAL, "there are too many samples");
+        }
+        let sys_clock: &dyn Clock = match offset.clockid {
+            CLOCK_REALTIME => RealTimeClock::get().as_ref(),
+            CLOCK_MONOTONIC => MonotonicClock::get().as_ref(),
+            CLOCK_MONOTONIC_RAW => MonotonicRawClock::get().as_ref(),
+            _ => return_errno_with_message!(Errno::EINVAL, "the system clock is not supported"),
+        };
+
+        for sample in offset.ts.iter_mut().take(offset.n_samples as usize) {
+            sample[0] = sys_clock.read_time().into();
+            sample[1] = self.clock.read_time()?.into();
+            sample[2] = sys_clock.read_time().into();
+        }
+
+        current_userspace!().write_val(arg, &offset)
+    }
+
+    fn sys_offset_precise(&self, arg: usize) -> Result<()> {
+        let Some(res) = self.clock.read_cross_timestamp() else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "cross timestamping is not supported");
+        };
+        let timestamp = res?;
+
+        let offset = c_ptp_sys_offset_precise {
+            device: timestamp.device.into(),
+            sys_realtime: timestamp.sys_realtime.into(),
+            sys_monoraw: timestamp.sys_monoraw.into(),
+            rsv: [0; 4],
+        };
+        current_userspace!().write_val(arg, &offset)
+    }
+}
+
+impl Pollable for PtpFile {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        // No external timestamp events are supported, so there is nothing to read.
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for PtpFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "external timestamps are not supported");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the PTP clock cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::PTP_CLOCK_GETCAPS | IoctlCmd::PTP_CLOCK_GETCAPS2 => self.get_caps(arg)?,
+            IoctlCmd::PTP_SYS_OFFSET | IoctlCmd::PTP_SYS_OFFSET2 => self.sys_offset(arg)?,
+            IoctlCmd::PTP_SYS_OFFSET_PRECISE | IoctlCmd::PTP_SYS_OFFSET_PRECISE2 => {
+                self.sys_offset_precise(arg)?
+            }
+            IoctlCmd::PTP_SYS_OFFSET_EXTENDED | IoctlCmd::PTP_SYS_OFFSET_EXTENDED2 => {
+                self.sys_offset_extended(arg)?
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        Ok(0)
+    }
+}
+
+const CLOCK_REALTIME: i32 = 0;
+const CLOCK_MONOTONIC: i32 = 1;
+const CLOCK_MONOTONIC_RAW: i32 = 4;
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/ptp_clock.h
+#[derive(Debug, Default, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_ptp_clock_time {
+    sec: i64,
+    nsec: u32,
+    reserved: u32,
+}
+
+impl From<Duration> for c_ptp_clock_time {
+    fn from(duration: Duration) -> Self {
+        Self {
+            sec: duration.as_secs() as i64,
+            nsec: duration.subsec_nanos(),
+            reserved: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_ptp_clock_caps {
+    max_adj: i32,
+    n_alarm: i32,
+    n_ext_ts: i32,
+    n_per_out: i32,
+    pps: i32,
+    n_pins: i32,
+    cross_timestamping: i32,
+    adjust_phase: i32,
+    max_phase_adj: i32,
+    rsv: [i32; 11],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_ptp_sys_offset {
+    n_samples: u32,
+    rsv: [u32; 3],
+    ts: [c_ptp_clock_time; 2 * PTP_MAX_SAMPLES as usize + 1],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_ptp_sys_offset_extended {
+    n_samples: u32,
+    clockid: i32,
+    rsv: [u32; 2],
+    ts: [[c_ptp_clock_time; 3]; PTP_MAX_SAMPLES as usize],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_ptp_sys_offset_precise {
+    device: c_ptp_clock_time,
+    sys_realtime: c_ptp_clock_time,
+    sys_monoraw: c_ptp_clock_time,
+    rsv: [u32; 4],
+}
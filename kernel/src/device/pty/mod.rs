@@ -4,7 +4,6 @@ use crate::{
     fs::{
         devpts::DevPts,
         fs_resolver::{FsPath, FsResolver},
-        path::Dentry,
         utils::{Inode, InodeMode, InodeType},
     },
     prelude::*,
@@ -14,9 +13,6 @@ use crate::{
 mod pty;
 
 pub use pty::{PtyMaster, PtySlave};
-use spin::Once;
-
-static DEV_PTS: Once<Dentry> = Once::new();
 
 pub fn init() -> Result<()> {
     let fs = FsResolver::new();
@@ -25,10 +21,9 @@ pub fn init() -> Result<()> {
     // Create the "pts" directory and mount devpts on it.
     let devpts_dentry =
         dev.new_fs_child("pts", InodeType::Dir, InodeMode::from_bits_truncate(0o755))?;
-    let devpts_mount_node = devpts_dentry.mount(DevPts::new())?;
-    let devpts = Dentry::new_fs_root(devpts_mount_node.clone());
-
-    DEV_PTS.call_once(|| devpts);
+    let devpts = DevPts::new();
+    let devpts_mount_node = devpts_dentry.mount(devpts.clone())?;
+    devpts.set_mount_node(&devpts_mount_node);
 
     // Create the "ptmx" symlink.
     let ptmx = dev.new_fs_child(
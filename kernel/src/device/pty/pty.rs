@@ -1,24 +1,28 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::format;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use ostd::task::Task;
 
 use crate::{
     current_userspace,
-    device::tty::{line_discipline::LineDiscipline, new_job_control_and_ldisc},
+    device::tty::{
+        ioctl::{tty_ioctl, TtyOps},
+        line_discipline::LineDiscipline,
+        new_job_control_and_ldisc,
+        termio::{KernelTermios, CC_C_CHAR, C_IFLAGS},
+    },
     events::IoEvents,
     fs::{
         device::{Device, DeviceId, DeviceType},
         devpts::DevPts,
         file_table::FdFlags,
-        fs_resolver::FsPath,
-        inode_handle::FileIo,
-        utils::{AccessMode, Inode, InodeMode, IoctlCmd},
+        inode_handle::{FileIo, InodeHandle},
+        utils::{AccessMode, CreationFlags, Inode, IoctlCmd, StatusFlags},
     },
     prelude::*,
     process::{
-        posix_thread::{AsPosixThread, AsThreadLocal},
+        posix_thread::AsThreadLocal,
         signal::{PollHandle, Pollable, Pollee},
         JobControl, Terminal,
     },
@@ -27,6 +31,24 @@ use crate::{
 
 const BUFFER_CAPACITY: usize = 4096;
 
+bitflags! {
+    /// The status reported to the master in the packet mode (i.e., `TIOCPKT_*` in Linux).
+    struct PacketStatus: u8 {
+        /// The input of the slave is flushed.
+        const FLUSHREAD  = 1 << 0;
+        /// The output of the slave is flushed.
+        const FLUSHWRITE = 1 << 1;
+        /// The output of the slave is stopped.
+        const STOP       = 1 << 2;
+        /// The output of the slave is restarted.
+        const START      = 1 << 3;
+        /// The stop and start characters are no longer `^S` and `^Q`.
+        const NOSTOP     = 1 << 4;
+        /// The stop and start characters are `^S` and `^Q` again.
+        const DOSTOP     = 1 << 5;
+    }
+}
+
 /// Pseudo terminal master.
 /// Internally, it has two buffers.
 /// One is inside ldisc, which is written by master and read by slave,
@@ -36,6 +58,12 @@ pub struct PtyMaster {
     index: u32,
     slave: Arc<PtySlave>,
     input: SpinLock<RingBuffer<u8>>,
+    /// Whether the slave is locked, in which case the slave cannot be opened
+    is_locked: AtomicBool,
+    /// Whether the packet mode is enabled
+    is_packet_mode: AtomicBool,
+    /// The pending status of the packet mode
+    packet_status: AtomicU8,
     /// The state of input buffer
     pollee: Pollee,
 }
@@ -45,6 +73,7 @@ impl PtyMaster {
         Arc::new_cyclic(move |master| {
             let (job_control, ldisc) = new_job_control_and_ldisc();
             let slave = Arc::new_cyclic(move |weak_self| PtySlave {
+                index,
                 ldisc,
                 job_control,
                 master: master.clone(),
@@ -56,6 +85,10 @@ impl PtyMaster {
                 index,
                 slave,
                 input: SpinLock::new(RingBuffer::new(BUFFER_CAPACITY)),
+                // Like Linux, the slave is locked until `unlockpt` is called.
+                is_locked: AtomicBool::new(true),
+                is_packet_mode: AtomicBool::new(false),
+                packet_status: AtomicU8::new(0),
                 pollee: Pollee::new(),
             }
         })
@@ -103,27 +136,67 @@ impl PtyMaster {
         poll_status
     }
 
+    /// Reports the status to the master if the packet mode is enabled.
+    ///
+    /// The status in `cleared` is discarded if it has not been read.
+    fn report_packet_status(&self, status: PacketStatus, cleared: PacketStatus) {
+        if !self.is_packet_mode.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let _ = self
+            .packet_status
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                Some(old & !cleared.bits() | status.bits())
+            });
+        self.pollee.notify(IoEvents::IN | IoEvents::PRI);
+    }
+
     fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let is_packet_mode = self.is_packet_mode.load(Ordering::Relaxed);
+
+        // In the packet mode, a pending status is read as a single byte.
+        if is_packet_mode {
+            let status = self.packet_status.load(Ordering::Relaxed);
+            if status != 0 {
+                writer.write_val(&status)?;
+                self.packet_status.fetch_and(!status, Ordering::Relaxed);
+                self.pollee.invalidate();
+                return Ok(1);
+            }
+        }
+
         let mut input = self.input.disable_irq().lock();
 
         if input.is_empty() {
             return_errno_with_message!(Errno::EAGAIN, "the buffer is empty");
         }
 
-        let read_len = input.read_fallible(writer)?;
+        let mut read_len = 0;
+        // In the packet mode, the data is preceded by a zero byte (i.e., `TIOCPKT_DATA`).
+        if is_packet_mode {
+            writer.write_val(&0u8)?;
+            read_len += 1;
+        }
+        read_len += input.read_fallible(writer)?;
         self.pollee.invalidate();
 
         Ok(read_len)
     }
 
     fn check_io_events(&self) -> IoEvents {
-        let input = self.input.disable_irq().lock();
+        let mut events = IoEvents::OUT;
 
-        if !input.is_empty() {
-            IoEvents::IN | IoEvents::OUT
-        } else {
-            IoEvents::OUT
+        if self.is_packet_mode.load(Ordering::Relaxed)
+            && self.packet_status.load(Ordering::Relaxed) != 0
+        {
+            events |= IoEvents::IN | IoEvents::PRI;
         }
+        if !self.input.disable_irq().lock().is_empty() {
+            events |= IoEvents::IN;
+        }
+
+        events
     }
 }
 
@@ -167,7 +240,7 @@ impl FileIo for PtyMaster {
         let mut input = self.input.lock();
         for character in buf {
             self.slave.ldisc.push_char(character, |content| {
-                for byte in content.as_bytes() {
+                for byte in content {
                     input.push_overwrite(*byte);
                 }
             });
@@ -179,42 +252,55 @@ impl FileIo for PtyMaster {
 
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         match cmd {
-            IoctlCmd::TCGETS
-            | IoctlCmd::TCSETS
-            | IoctlCmd::TIOCGPTN
-            | IoctlCmd::TIOCGWINSZ
-            | IoctlCmd::TIOCSWINSZ => return self.slave.ioctl(cmd, arg),
+            IoctlCmd::TIOCGPTN => {
+                let idx = self.index();
+                current_userspace!().write_val(arg, &idx)?;
+            }
             IoctlCmd::TIOCSPTLCK => {
-                // TODO: lock/unlock pty
+                let is_locked = current_userspace!().read_val::<i32>(arg)? != 0;
+                self.is_locked.store(is_locked, Ordering::Relaxed);
+            }
+            IoctlCmd::TIOCGPTLCK => {
+                let is_locked = self.is_locked.load(Ordering::Relaxed) as i32;
+                current_userspace!().write_val(arg, &is_locked)?;
+            }
+            IoctlCmd::TIOCPKT => {
+                let is_packet_mode = current_userspace!().read_val::<i32>(arg)? != 0;
+                self.packet_status.store(0, Ordering::Relaxed);
+                self.is_packet_mode.store(is_packet_mode, Ordering::Relaxed);
+            }
+            IoctlCmd::TIOCGPKT => {
+                let is_packet_mode = self.is_packet_mode.load(Ordering::Relaxed) as i32;
+                current_userspace!().write_val(arg, &is_packet_mode)?;
             }
             IoctlCmd::TIOCGPTPEER => {
-                let current_task = Task::current().unwrap();
-                let posix_thread = current_task.as_posix_thread().unwrap();
-                let thread_local = current_task.as_thread_local().unwrap();
+                // The argument is the flags to open the slave, like those of `open`.
+                let flags = arg as u32;
+                let access_mode = AccessMode::from_u32(flags)?;
+                let status_flags = StatusFlags::from_bits_truncate(flags);
+                let fd_flags = if CreationFlags::from_bits_truncate(flags)
+                    .contains(CreationFlags::O_CLOEXEC)
+                {
+                    FdFlags::CLOEXEC
+                } else {
+                    FdFlags::empty()
+                };
 
-                // TODO: deal with open options
+                // Open the slave in the devpts instance of the master, regardless of where the
+                // devpts instances are mounted in the file system of the current process.
                 let slave = {
-                    let slave_name = {
-                        let devpts_path = super::DEV_PTS.get().unwrap().abs_path();
-                        format!("{}/{}", devpts_path, self.index())
-                    };
-
-                    let fs_path = FsPath::try_from(slave_name.as_str())?;
-
-                    let inode_handle = {
-                        let fs = posix_thread.fs().resolver().read();
-                        let flags = AccessMode::O_RDWR as u32;
-                        let mode = (InodeMode::S_IRUSR | InodeMode::S_IWUSR).bits();
-                        fs.open(&fs_path, flags, mode)?
-                    };
-                    Arc::new(inode_handle)
+                    let fs = self.ptmx.fs();
+                    let devpts = fs.downcast_ref::<DevPts>().unwrap();
+                    let dentry = devpts.slave_dentry(self.index)?;
+                    Arc::new(InodeHandle::new(dentry, access_mode, status_flags)?)
                 };
 
                 let fd = {
+                    let current_task = Task::current().unwrap();
+                    let thread_local = current_task.as_thread_local().unwrap();
                     let file_table = thread_local.borrow_file_table();
                     let mut file_table_locked = file_table.unwrap().write();
-                    // TODO: deal with the O_CLOEXEC flag
-                    file_table_locked.insert(slave, FdFlags::empty())
+                    file_table_locked.insert(slave, fd_flags)
                 };
                 return Ok(fd);
             }
@@ -222,7 +308,8 @@ impl FileIo for PtyMaster {
                 let len = self.input.lock().len() as i32;
                 current_userspace!().write_val(arg, &len)?;
             }
-            _ => (self.slave.clone() as Arc<dyn Terminal>).job_ioctl(cmd, arg, true)?,
+            // The commands about the termios and the window size operate on the slave side.
+            _ => return tty_ioctl(self.slave.clone(), cmd, arg, true),
         }
 
         Ok(0)
@@ -236,10 +323,15 @@ impl Drop for PtyMaster {
 
         let index = self.index;
         devpts.remove_slave(index);
+
+        // Closing the master hangs up the slave.
+        self.slave.ldisc.hang_up();
+        (self.slave.clone() as Arc<dyn Terminal>).hang_up();
     }
 }
 
 pub struct PtySlave {
+    index: u32,
     ldisc: Arc<LineDiscipline>,
     job_control: Arc<JobControl>,
     master: Weak<PtyMaster>,
@@ -248,11 +340,14 @@ pub struct PtySlave {
 
 impl PtySlave {
     pub fn index(&self) -> u32 {
-        self.master().index()
+        self.index
     }
 
-    fn master(&self) -> Arc<PtyMaster> {
-        self.master.upgrade().unwrap()
+    /// Returns the master, or fails with `EIO` if the master has been closed.
+    fn master(&self) -> Result<Arc<PtyMaster>> {
+        self.master
+            .upgrade()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the pty master has been closed"))
     }
 }
 
@@ -264,6 +359,14 @@ impl Device for PtySlave {
     fn id(&self) -> crate::fs::device::DeviceId {
         DeviceId::new(88, self.index())
     }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        if self.master()?.is_locked.load(Ordering::Relaxed) {
+            return_errno_with_message!(Errno::EIO, "the pty is locked");
+        }
+
+        Ok(None)
+    }
 }
 
 impl Terminal for PtySlave {
@@ -274,7 +377,11 @@ impl Terminal for PtySlave {
 
 impl Pollable for PtySlave {
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
-        self.master().slave_poll(mask, poller)
+        match self.master.upgrade() {
+            Some(master) => master.slave_poll(mask, poller),
+            // The slave has been hung up.
+            None => self.ldisc.poll(mask, poller),
+        }
     }
 }
 
@@ -290,49 +397,75 @@ impl FileIo for PtySlave {
     fn write(&self, reader: &mut VmReader) -> Result<usize> {
         let buf = reader.collect()?;
         let write_len = buf.len();
-        let master = self.master();
-        for ch in buf {
-            // do we need to add '\r' here?
-            if ch == b'\n' {
-                master.slave_push_char(b'\r');
-                master.slave_push_char(b'\n');
-            } else {
-                master.slave_push_char(ch);
-            }
+        let master = self.master()?;
+        for ch in self.ldisc.process_output(&buf) {
+            master.slave_push_char(ch);
         }
         Ok(write_len)
     }
 
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         match cmd {
-            IoctlCmd::TCGETS => {
-                let termios = self.ldisc.termios();
-                current_userspace!().write_val(arg, &termios)?;
-            }
-            IoctlCmd::TCSETS => {
-                let termios = current_userspace!().read_val(arg)?;
-                self.ldisc.set_termios(termios);
-            }
             IoctlCmd::TIOCGPTN => {
                 let idx = self.index();
                 current_userspace!().write_val(arg, &idx)?;
+                Ok(0)
             }
-            IoctlCmd::TIOCGWINSZ => {
-                let winsize = self.ldisc.window_size();
-                current_userspace!().write_val(arg, &winsize)?;
-            }
-            IoctlCmd::TIOCSWINSZ => {
-                let winsize = current_userspace!().read_val(arg)?;
-                self.ldisc.set_window_size(winsize);
-            }
-            IoctlCmd::FIONREAD => {
-                let buffer_len = self.ldisc.buffer_len() as i32;
-                current_userspace!().write_val(arg, &buffer_len)?;
-            }
-            _ => (self.weak_self.upgrade().unwrap() as Arc<dyn Terminal>)
-                .job_ioctl(cmd, arg, false)?,
+            _ => tty_ioctl(self.weak_self.upgrade().unwrap(), cmd, arg, false),
         }
+    }
+}
 
-        Ok(0)
+impl TtyOps for PtySlave {
+    fn ldisc(&self) -> &LineDiscipline {
+        &self.ldisc
     }
+
+    fn set_termios(&self, termios: KernelTermios) {
+        let old_termios = self.ldisc.termios();
+        self.ldisc.set_termios(termios);
+
+        let is_stop_enabled = is_default_stop_start(&termios);
+        if is_stop_enabled == is_default_stop_start(&old_termios) {
+            return;
+        }
+        let Ok(master) = self.master() else {
+            return;
+        };
+        let all_stop = PacketStatus::NOSTOP | PacketStatus::DOSTOP;
+        if is_stop_enabled {
+            master.report_packet_status(PacketStatus::DOSTOP, all_stop);
+        } else {
+            master.report_packet_status(PacketStatus::NOSTOP, all_stop);
+        }
+    }
+
+    fn flush_input(&self) {
+        self.ldisc.drain_input();
+        if let Ok(master) = self.master() {
+            master.report_packet_status(PacketStatus::FLUSHREAD, PacketStatus::empty());
+        }
+    }
+
+    fn flush_output(&self) {
+        let Ok(master) = self.master() else {
+            return;
+        };
+        master.input.lock().clear();
+        master.pollee.invalidate();
+        master.report_packet_status(PacketStatus::FLUSHWRITE, PacketStatus::empty());
+    }
+
+    fn output_len(&self) -> usize {
+        self.master
+            .upgrade()
+            .map_or(0, |master| master.input.lock().len())
+    }
+}
+
+/// Returns whether the output can be stopped and restarted with `^S` and `^Q`.
+fn is_default_stop_start(termios: &KernelTermios) -> bool {
+    termios.iflags().contains(C_IFLAGS::IXON)
+        && *termios.get_special_char(CC_C_CHAR::VSTOP) == CC_C_CHAR::VSTOP.default_char()
+        && *termios.get_special_char(CC_C_CHAR::VSTART) == CC_C_CHAR::VSTART.default_char()
 }
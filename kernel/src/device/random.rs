@@ -10,18 +10,11 @@ use crate::{
     },
     prelude::*,
     process::signal::{PollHandle, Pollable},
-    util::random::getrandom,
+    util::random,
 };
 
 pub struct Random;
 
-impl Random {
-    pub fn getrandom(buf: &mut [u8]) -> Result<usize> {
-        getrandom(buf)?;
-        Ok(buf.len())
-    }
-}
-
 impl Device for Random {
     fn type_(&self) -> DeviceType {
         DeviceType::CharDevice
@@ -46,13 +39,18 @@ impl Pollable for Random {
 
 impl FileIo for Random {
     fn read(&self, writer: &mut VmWriter) -> Result<usize> {
-        let mut buf = vec![0; writer.avail()];
-        let size = Self::getrandom(buf.as_mut_slice());
-        writer.write_fallible(&mut buf.as_slice().into())?;
-        size
+        // TODO: Support `O_NONBLOCK`. Blocking is usually short since the CRNG
+        // generates entropy from the CPU jitter while waiting.
+        random::wait_until_ready()?;
+        random::fill_writer(writer)
     }
 
     fn write(&self, reader: &mut VmReader) -> Result<usize> {
-        Ok(reader.remain())
+        // The written data is mixed into the input pool without crediting any
+        // entropy, as Linux does.
+        let mut buf = vec![0; reader.remain()];
+        let len = reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+        random::add_device_randomness(&buf[..len]);
+        Ok(len)
     }
 }
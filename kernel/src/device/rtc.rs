@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The real-time clock (RTC), which is exposed as `/dev/rtc0` (and `/dev/rtc`).
+//!
+//! The RTC keeps the time while the system is powered off. The real time is initialized from it
+//! at boot, and the user space (e.g., `hwclock`) reads or sets it through the ioctls.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/rtc.html>
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_time::RtcError;
+use time::{Date, Month};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+    },
+};
+
+/// The major device number of the RTCs.
+///
+/// Linux allocates the number dynamically, so a fixed number in the dynamic range is used.
+pub(super) const RTC_MAJOR: u32 = 252;
+
+/// Whether the RTC is opened, since it can only be opened once at a time like Linux.
+static IS_OPENED: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn init() -> Result<()> {
+    if !aster_time::has_rtc() {
+        return Ok(());
+    }
+
+    add_node(Arc::new(Rtc), "rtc0", "rtc")?;
+    add_node(Arc::new(Rtc), "rtc", "rtc")?;
+
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    if minor != 0 || !aster_time::has_rtc() {
+        return_errno_with_message!(Errno::ENODEV, "the RTC does not exist");
+    }
+    Ok(Arc::new(Rtc))
+}
+
+struct Rtc;
+
+impl Device for Rtc {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(RTC_MAJOR, 0)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        if IS_OPENED.swap(true, Ordering::Acquire) {
+            return_errno_with_message!(Errno::EBUSY, "the RTC is already opened");
+        }
+        Ok(Some(Arc::new(RtcFile)))
+    }
+}
+
+impl Pollable for Rtc {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for Rtc {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the RTC is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the RTC is not opened");
+    }
+}
+
+/// An opened RTC.
+struct RtcFile;
+
+impl RtcFile {
+    fn read_time(&self, arg: usize) -> Result<()> {
+        let time = aster_time::read_rtc().map_err(rtc_error_to_errno)?;
+        current_userspace!().write_val(arg, &c_rtc_time::try_from(time)?)
+    }
+
+    fn set_time(&self, arg: usize) -> Result<()> {
+        // Like Linux, `EACCES` instead of `EPERM` is returned.
+        let is_capable = current_thread!().as_posix_thread().is_some_and(|thread| {
+            thread
+                .credentials()
+                .effective_capset()
+                .contains(CapSet::SYS_TIME)
+        });
+        if !is_capable {
+            return_errno_with_message!(Errno::EACCES, "setting the RTC requires CAP_SYS_TIME");
+        }
+
+        let time: c_rtc_time = current_userspace!().read_val(arg)?;
+        aster_time::write_rtc(time.try_into()?).map_err(rtc_error_to_errno)
+    }
+}
+
+impl Drop for RtcFile {
+    fn drop(&mut self) {
+        IS_OPENED.store(false, Ordering::Release);
+    }
+}
+
+impl Pollable for RtcFile {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        // No RTC interrupts are supported, so there is nothing to read.
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for RtcFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the RTC interrupts are not supported");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the RTC cannot be written");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::RTC_RD_TIME => self.read_time(arg)?,
+            IoctlCmd::RTC_SET_TIME => self.set_time(arg)?,
+            // The user space (e.g., `hwclock`) falls back to polling the time if the update
+            // interrupts cannot be enabled with `ENOTTY`.
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        Ok(0)
+    }
+}
+
+fn rtc_error_to_errno(err: RtcError) -> Error {
+    match err {
+        RtcError::NotFound => Error::with_message(Errno::ENODEV, "the RTC does not exist"),
+        RtcError::InvalidTime => {
+            Error::with_message(Errno::EINVAL, "the time cannot be represented by the RTC")
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/rtc.h
+#[derive(Debug, Default, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_rtc_time {
+    tm_sec: i32,
+    tm_min: i32,
+    tm_hour: i32,
+    tm_mday: i32,
+    /// The month, in the range 0 to 11.
+    tm_mon: i32,
+    /// The number of years since 1900.
+    tm_year: i32,
+    /// The day of the week, in the range 0 (Sunday) to 6.
+    tm_wday: i32,
+    /// The day of the year, in the range 0 to 365.
+    tm_yday: i32,
+    tm_isdst: i32,
+}
+
+impl TryFrom<aster_time::SystemTime> for c_rtc_time {
+    type Error = Error;
+
+    fn try_from(time: aster_time::SystemTime) -> Result<Self> {
+        let Ok(month) = Month::try_from(time.month) else {
+            return_errno_with_message!(Errno::EIO, "the RTC reports an invalid month");
+        };
+        let Ok(date) = Date::from_calendar_date(time.year as i32, month, time.day) else {
+            return_errno_with_message!(Errno::EIO, "the RTC reports an invalid date");
+        };
+
+        Ok(Self {
+            tm_sec: time.second as i32,
+            tm_min: time.minute as i32,
+            tm_hour: time.hour as i32,
+            tm_mday: time.day as i32,
+            tm_mon: time.month as i32 - 1,
+            tm_year: time.year as i32 - 1900,
+            tm_wday: date.weekday().number_days_from_sunday() as i32,
+            tm_yday: date.ordinal() as i32 - 1,
+            tm_isdst: 0,
+        })
+    }
+}
+
+impl TryFrom<c_rtc_time> for aster_time::SystemTime {
+    type Error = Error;
+
+    /// Converts the time like `rtc_valid_tm` in Linux, which ignores the day of the week and the
+    /// day of the year.
+    fn try_from(time: c_rtc_time) -> Result<Self> {
+        let year = time.tm_year.checked_add(1900).unwrap_or(i32::MAX);
+        if !(1970..=9999).contains(&year) {
+            return_errno_with_message!(Errno::EINVAL, "the year is out of range");
+        }
+        let Some(month) = u8::try_from(time.tm_mon)
+            .ok()
+            .and_then(|month| Month::try_from(month.checked_add(1)?).ok())
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the month is invalid");
+        };
+        let Some(day) = u8::try_from(time.tm_mday)
+            .ok()
+            .filter(|day| Date::from_calendar_date(year, month, *day).is_ok())
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the day is invalid");
+        };
+        if !(0..24).contains(&time.tm_hour)
+            || !(0..60).contains(&time.tm_min)
+            || !(0..60).contains(&time.tm_sec)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the time of the day is invalid");
+        }
+
+        Ok(Self {
+            year: year as u16,
+            month: month as u8,
+            day,
+            hour: time.tm_hour as u8,
+            minute: time.tm_min as u8,
+            second: time.tm_sec as u8,
+            nanos: 0,
+        })
+    }
+}
@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The serial terminals (`/dev/ttySN`), which give userspace access to the serial ports.
+//!
+//! Each terminal has its own line discipline and job control, so it is independent of the
+//! console. The termios of a terminal is applied to its port, so the baud rate, the character
+//! size, the parity, and the flow control can be changed at runtime.
+
+use aster_serial::{Parity, SerialConfig, SerialListener, SerialPort, StopBits};
+
+use super::tty::{
+    ioctl::{tty_ioctl, TtyOps},
+    line_discipline::{LineDiscipline, BUFFER_CAPACITY},
+    new_job_control_and_ldisc,
+    termio::{KernelTermios, CC_C_CHAR, C_CFLAGS_BAUD, C_CFLAGS_CSIZE, C_IFLAGS},
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::{
+        signal::{PollHandle, Pollable, Pollee},
+        JobControl, Terminal,
+    },
+};
+
+/// The major device number of the serial terminals, which is the same as Linux.
+pub(super) const SERIAL_MAJOR: u32 = 4;
+/// The minor device number of `ttyS0`.
+const SERIAL_MINOR_BASE: u32 = 64;
+
+/// The port is throttled when the read buffer has less room than this.
+const THROTTLE_ROOM: usize = 128;
+/// The port is unthrottled when the read buffer has more room than this.
+const UNTHROTTLE_ROOM: usize = 1024;
+
+/// The serial terminals, which are indexed by their line numbers.
+static SERIAL_TTYS: Mutex<BTreeMap<u32, Arc<SerialTty>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init() -> Result<()> {
+    for (line, port) in aster_serial::all_ports() {
+        let tty = SerialTty::new(line, port);
+        add_node(tty.clone(), &format!("ttyS{}", line), "tty")?;
+        SERIAL_TTYS.lock().insert(line, tty);
+    }
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let tty = minor
+        .checked_sub(SERIAL_MINOR_BASE)
+        .and_then(|line| SERIAL_TTYS.lock().get(&line).cloned());
+    let Some(tty) = tty else {
+        return_errno_with_message!(Errno::ENODEV, "the serial port does not exist");
+    };
+    Ok(tty)
+}
+
+struct SerialTty {
+    line: u32,
+    port: Arc<SerialPort>,
+    ldisc: Arc<LineDiscipline>,
+    job_control: Arc<JobControl>,
+    /// The state of the transmit buffer of the port.
+    pollee: Pollee,
+    weak_self: Weak<Self>,
+}
+
+impl SerialTty {
+    fn new(line: u32, port: Arc<SerialPort>) -> Arc<Self> {
+        let (job_control, ldisc) = new_job_control_and_ldisc();
+
+        // Keep the baud rate of the port, which may be configured by the firmware.
+        let mut termios = ldisc.termios();
+        let baud =
+            C_CFLAGS_BAUD::from_speed(port.config().baud_rate).unwrap_or(C_CFLAGS_BAUD::B9600);
+        termios.cflags_mut().set_cbaud(baud);
+        ldisc.set_termios(termios);
+
+        let tty = Arc::new_cyclic(|weak_self| Self {
+            line,
+            port,
+            ldisc,
+            job_control,
+            pollee: Pollee::new(),
+            weak_self: weak_self.clone(),
+        });
+        tty.apply_termios(&termios);
+        tty.port
+            .set_listener(tty.weak_self.clone() as Weak<dyn SerialListener>);
+
+        tty
+    }
+
+    fn apply_termios(&self, termios: &KernelTermios) {
+        let config = config_from_termios(termios, &self.port.config());
+        if let Err(err) = self.port.set_config(&config) {
+            // Like Linux, the unsupported settings are ignored instead of being reported.
+            warn!(
+                "ttyS{}: Unsupported configuration {:?}: {:?}",
+                self.line, config, err
+            );
+        }
+    }
+}
+
+fn config_from_termios(termios: &KernelTermios, old_config: &SerialConfig) -> SerialConfig {
+    let cflags = termios.cflags();
+    let iflags = termios.iflags();
+
+    let baud_rate = cflags
+        .cbaud()
+        .map_or(old_config.baud_rate, |baud| baud.speed());
+    let data_bits = match cflags.csize() {
+        Ok(C_CFLAGS_CSIZE::CS5) => 5,
+        Ok(C_CFLAGS_CSIZE::CS6) => 6,
+        Ok(C_CFLAGS_CSIZE::CS7) => 7,
+        Ok(C_CFLAGS_CSIZE::CS8) | Err(_) => 8,
+    };
+    let parity = match (cflags.parenb(), cflags.cmspar(), cflags.parodd()) {
+        (false, _, _) => Parity::None,
+        (true, false, true) => Parity::Odd,
+        (true, false, false) => Parity::Even,
+        (true, true, true) => Parity::Mark,
+        (true, true, false) => Parity::Space,
+    };
+    let stop_bits = if cflags.cstopb() {
+        StopBits::Two
+    } else {
+        StopBits::One
+    };
+
+    SerialConfig {
+        baud_rate,
+        data_bits,
+        parity,
+        stop_bits,
+        receiver_enabled: cflags.cread(),
+        rts_cts: cflags.crtscts(),
+        ixon: iflags.contains(C_IFLAGS::IXON),
+        ixoff: iflags.contains(C_IFLAGS::IXOFF),
+        start_char: *termios.get_special_char(CC_C_CHAR::VSTART),
+        stop_char: *termios.get_special_char(CC_C_CHAR::VSTOP),
+    }
+}
+
+impl SerialListener for SerialTty {
+    fn on_receive(&self, bytes: &[u8]) {
+        for ch in bytes {
+            self.ldisc.push_char(*ch, |content| {
+                self.port.write(content);
+            });
+        }
+
+        if BUFFER_CAPACITY - self.ldisc.buffer_len() < THROTTLE_ROOM {
+            self.port.throttle();
+        }
+    }
+
+    fn on_transmit_ready(&self) {
+        self.pollee.notify(IoEvents::OUT);
+    }
+}
+
+impl Pollable for SerialTty {
+    fn poll(&self, mask: IoEvents, mut poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = self.ldisc.poll(mask, poller.as_deref_mut());
+        events
+            | self.pollee.poll_with(mask, poller, || {
+                if self.port.can_write() {
+                    IoEvents::OUT
+                } else {
+                    IoEvents::empty()
+                }
+            })
+    }
+}
+
+impl FileIo for SerialTty {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut buf = vec![0; writer.avail()];
+        self.job_control.wait_until_in_foreground()?;
+        let read_len = self.ldisc.read(buf.as_mut_slice())?;
+        buf.truncate(read_len);
+        writer.write_fallible(&mut buf.as_slice().into())?;
+
+        if BUFFER_CAPACITY - self.ldisc.buffer_len() > UNTHROTTLE_ROOM {
+            self.port.unthrottle();
+        }
+        Ok(read_len)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let buf = reader.collect()?;
+        let write_len = buf.len();
+        let output = self.ldisc.process_output(&buf);
+
+        let mut written = 0;
+        while written < output.len() {
+            let result = self.wait_events(IoEvents::OUT, None, || {
+                let len = self.port.write(&output[written..]);
+                if len == 0 {
+                    self.pollee.invalidate();
+                    return_errno_with_message!(Errno::EAGAIN, "the transmit buffer is full");
+                }
+                Ok(len)
+            });
+            match result {
+                Ok(len) => written += len,
+                // Report the partial write if the waiting is interrupted.
+                Err(_) if written > 0 => return Ok(written.min(write_len)),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(write_len)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        tty_ioctl(self.weak_self.upgrade().unwrap(), cmd, arg, false)
+    }
+}
+
+impl TtyOps for SerialTty {
+    fn ldisc(&self) -> &LineDiscipline {
+        &self.ldisc
+    }
+
+    fn set_termios(&self, termios: KernelTermios) {
+        self.ldisc.set_termios(termios);
+        self.apply_termios(&termios);
+    }
+
+    /// Waits until all the bytes in the transmit buffer are sent.
+    fn drain_output(&self) -> Result<()> {
+        self.wait_events(IoEvents::OUT, None, || {
+            if self.port.is_tx_empty() {
+                Ok(())
+            } else {
+                return_errno_with_message!(Errno::EAGAIN, "the output is not drained")
+            }
+        })
+    }
+
+    fn flush_input(&self) {
+        self.ldisc.drain_input();
+        self.port.unthrottle();
+    }
+}
+
+impl Terminal for SerialTty {
+    fn job_control(&self) -> &JobControl {
+        &self.job_control
+    }
+}
+
+impl Device for SerialTty {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(SERIAL_MAJOR, SERIAL_MINOR_BASE + self.line)
+    }
+}
@@ -0,0 +1,1459 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCM devices of the sound cards (`/dev/snd/pcmCxDyp`), which implement a subset of the ALSA
+//! PCM interface for playback.
+//!
+//! The audio data is written to a ring buffer, either with `write`/`SNDRV_PCM_IOCTL_WRITEI_FRAMES`
+//! or through the buffer mapped by `mmap`. The buffer is divided into periods, and the hardware
+//! pointer advances by a period each time the device has played one. The status and the control
+//! records cannot be mapped, so the users exchange the pointers with `SNDRV_PCM_IOCTL_SYNC_PTR`,
+//! which is what alsa-lib falls back to.
+//!
+//! Reference: <https://www.alsa-project.org/alsa-doc/alsa-lib/pcm.html>
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_rights::Rights;
+use aster_sound::{PcmFormat, PcmInfo, PcmListener, PcmParams, PcmStream, SoundCard, SoundError};
+use ostd::mm::{DmaDirection, DmaStream, FrameAllocOptions, Infallible, UFrame, USegment, VmIo};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+    vm::vmo::{Pager, Vmo, VmoOptions},
+};
+
+/// The major device number of the sound devices.
+pub(super) const SOUND_MAJOR: u32 = 116;
+/// The number of the minor device numbers of a card.
+const MINORS_PER_CARD: u32 = 32;
+/// The first minor device number of the playback PCM devices in a card.
+const PCM_PLAYBACK_MINOR_BASE: u32 = 16;
+/// The maximum number of the playback PCM devices of a card.
+const MAX_PCMS_PER_CARD: u32 = 16;
+
+/// The version of the PCM interface, which is the same as `SNDRV_PCM_VERSION` of Linux 6.x.
+const SNDRV_PCM_VERSION: u32 = 0x2000f;
+
+/// The minimum size of a period in bytes.
+const PERIOD_BYTES_MIN: usize = 64;
+
+// The offsets of the regions that can be mapped.
+const SNDRV_PCM_MMAP_OFFSET_DATA: usize = 0x00000000;
+const SNDRV_PCM_MMAP_OFFSET_STATUS: usize = 0x80000000;
+const SNDRV_PCM_MMAP_OFFSET_CONTROL: usize = 0x81000000;
+
+// The access types.
+const SNDRV_PCM_ACCESS_MMAP_INTERLEAVED: u32 = 0;
+const SNDRV_PCM_ACCESS_RW_INTERLEAVED: u32 = 3;
+
+const SNDRV_PCM_SUBFORMAT_STD: u32 = 0;
+
+// The information flags of the hardware parameters.
+const SNDRV_PCM_INFO_MMAP: u32 = 0x00000001;
+const SNDRV_PCM_INFO_MMAP_VALID: u32 = 0x00000002;
+const SNDRV_PCM_INFO_INTERLEAVED: u32 = 0x00000100;
+
+// The hardware parameters that are masks.
+const SNDRV_PCM_HW_PARAM_ACCESS: usize = 0;
+const SNDRV_PCM_HW_PARAM_FORMAT: usize = 1;
+const SNDRV_PCM_HW_PARAM_SUBFORMAT: usize = 2;
+
+// The hardware parameters that are intervals.
+const SNDRV_PCM_HW_PARAM_SAMPLE_BITS: usize = 8;
+const SNDRV_PCM_HW_PARAM_FRAME_BITS: usize = 9;
+const SNDRV_PCM_HW_PARAM_CHANNELS: usize = 10;
+const SNDRV_PCM_HW_PARAM_RATE: usize = 11;
+const SNDRV_PCM_HW_PARAM_PERIOD_TIME: usize = 12;
+const SNDRV_PCM_HW_PARAM_PERIOD_SIZE: usize = 13;
+const SNDRV_PCM_HW_PARAM_PERIOD_BYTES: usize = 14;
+const SNDRV_PCM_HW_PARAM_PERIODS: usize = 15;
+const SNDRV_PCM_HW_PARAM_BUFFER_TIME: usize = 16;
+const SNDRV_PCM_HW_PARAM_BUFFER_SIZE: usize = 17;
+const SNDRV_PCM_HW_PARAM_BUFFER_BYTES: usize = 18;
+const SNDRV_PCM_HW_PARAM_TICK_TIME: usize = 19;
+const SNDRV_PCM_HW_PARAM_FIRST_INTERVAL: usize = SNDRV_PCM_HW_PARAM_SAMPLE_BITS;
+const SNDRV_PCM_HW_PARAM_LAST_INTERVAL: usize = SNDRV_PCM_HW_PARAM_TICK_TIME;
+
+/// The bits of all the parameters in `rmask` and `cmask`.
+const ALL_PARAMS_MASK: u32 = 0b111
+    | (((1 << (SNDRV_PCM_HW_PARAM_LAST_INTERVAL - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL + 1)) - 1)
+        << SNDRV_PCM_HW_PARAM_FIRST_INTERVAL);
+
+// The flags of the intervals.
+const INTERVAL_OPENMIN: u32 = 1 << 0;
+const INTERVAL_OPENMAX: u32 = 1 << 1;
+const INTERVAL_INTEGER: u32 = 1 << 2;
+const INTERVAL_EMPTY: u32 = 1 << 3;
+
+// The flags of `SNDRV_PCM_IOCTL_SYNC_PTR`.
+const SNDRV_PCM_SYNC_PTR_APPL: u32 = 1 << 1;
+const SNDRV_PCM_SYNC_PTR_AVAIL_MIN: u32 = 1 << 2;
+
+const SNDRV_PCM_STREAM_PLAYBACK: i32 = 0;
+const SNDRV_PCM_TSTAMP_TYPE_LAST: i32 = 2;
+
+const MICROS_PER_SEC: u64 = 1_000_000;
+
+static PCM_DEVICES: Mutex<BTreeMap<u32, Arc<PcmDev>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn init() -> Result<()> {
+    for card in aster_sound::all_cards() {
+        if card.pcms().len() > MAX_PCMS_PER_CARD as usize {
+            warn!(
+                "sound card {} has too many PCM streams, only {} are added",
+                card.index(),
+                MAX_PCMS_PER_CARD
+            );
+        }
+
+        for index in 0..card.pcms().len().min(MAX_PCMS_PER_CARD as usize) {
+            let device = Arc::new(PcmDev {
+                card: card.clone(),
+                index: index as u32,
+                is_opened: AtomicBool::new(false),
+            });
+            PCM_DEVICES.lock().insert(device.minor(), device.clone());
+            add_node(
+                device,
+                &format!("snd/pcmC{}D{}p", card.index(), index),
+                "sound",
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = PCM_DEVICES.lock().get(&minor).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the sound device does not exist");
+    };
+    Ok(device)
+}
+
+/// A playback PCM device.
+struct PcmDev {
+    card: Arc<SoundCard>,
+    /// The index of the stream in the card, which is the PCM device number.
+    index: u32,
+    /// Whether the device is opened. Like Linux, a PCM device can be opened only once.
+    is_opened: AtomicBool,
+}
+
+impl PcmDev {
+    fn minor(&self) -> u32 {
+        self.card.index() * MINORS_PER_CARD + PCM_PLAYBACK_MINOR_BASE + self.index
+    }
+
+    fn stream(&self) -> &Arc<dyn PcmStream> {
+        &self.card.pcms()[self.index as usize]
+    }
+}
+
+impl Device for PcmDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(SOUND_MAJOR, self.minor())
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        if self.is_opened.swap(true, Ordering::Acquire) {
+            return_errno_with_message!(Errno::EBUSY, "the PCM device is opened");
+        }
+
+        let device = PCM_DEVICES.lock().get(&self.minor()).cloned().unwrap();
+        let file = Arc::new(PcmFile {
+            device,
+            op_lock: Mutex::new(()),
+            runtime: SpinLock::new(Runtime::new()),
+            pollee: Pollee::new(),
+        });
+        let listener: Weak<dyn PcmListener> = Arc::downgrade(&file) as _;
+        file.stream().set_listener(listener);
+        Ok(Some(file))
+    }
+}
+
+impl Pollable for PcmDev {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for PcmDev {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the PCM device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the PCM device is not opened");
+    }
+}
+
+/// The states of a PCM stream, which are the same as `SNDRV_PCM_STATE_*` of Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+enum PcmState {
+    /// The hardware parameters are not set.
+    Open = 0,
+    /// The hardware parameters are set.
+    Setup = 1,
+    /// The stream is ready to start.
+    Prepared = 2,
+    Running = 3,
+    /// The stream has run out of the data.
+    Xrun = 4,
+    /// The stream is playing the remaining data before it stops.
+    Draining = 5,
+}
+
+/// The hardware parameters that are chosen by `SNDRV_PCM_IOCTL_HW_PARAMS`.
+#[derive(Debug, Clone, Copy)]
+struct HwParams {
+    access: u32,
+    format: PcmFormat,
+    channels: u32,
+    rate: u32,
+    /// The size of a period in frames.
+    period_size: u64,
+    periods: u64,
+}
+
+impl HwParams {
+    fn frame_bytes(&self) -> usize {
+        (self.format.physical_width() / 8 * self.channels) as usize
+    }
+
+    /// Returns the size of the buffer in frames.
+    fn buffer_size(&self) -> u64 {
+        self.period_size * self.periods
+    }
+
+    fn buffer_bytes(&self) -> usize {
+        self.buffer_size() as usize * self.frame_bytes()
+    }
+
+    fn to_pcm_params(self) -> PcmParams {
+        PcmParams {
+            format: self.format,
+            channels: self.channels,
+            rate: self.rate,
+            period_bytes: self.period_size as usize * self.frame_bytes(),
+            buffer_bytes: self.buffer_bytes(),
+        }
+    }
+}
+
+/// The ring buffer of the audio data.
+///
+/// The buffer is shared by the device, which reads it with DMA, and the user space, which maps it
+/// through the VMO.
+#[derive(Clone)]
+struct PcmBuffer {
+    dma: DmaStream,
+    vmo: Arc<Vmo<Rights>>,
+}
+
+impl PcmBuffer {
+    fn new(size: usize) -> Result<Self> {
+        let nr_pages = size.div_ceil(PAGE_SIZE);
+        let segment: USegment = FrameAllocOptions::new().alloc_segment(nr_pages)?.into();
+        let frames = segment.clone().collect();
+        let dma = DmaStream::map(segment, DmaDirection::ToDevice, false)
+            .map_err(|_| Error::with_message(Errno::ENOMEM, "the PCM buffer cannot be mapped"))?;
+        let vmo = VmoOptions::<Rights>::new(nr_pages * PAGE_SIZE)
+            .pager(Arc::new(PcmBufferPager { frames }))
+            .alloc()?;
+        Ok(Self {
+            dma,
+            vmo: Arc::new(vmo),
+        })
+    }
+}
+
+/// The pager that provides the frames of the PCM buffer to its VMO.
+struct PcmBufferPager {
+    frames: Vec<UFrame>,
+}
+
+impl Pager for PcmBufferPager {
+    fn commit_page(&self, idx: usize) -> Result<UFrame> {
+        let Some(frame) = self.frames.get(idx) else {
+            return_errno_with_message!(Errno::EINVAL, "the page is out of the PCM buffer");
+        };
+        Ok(frame.clone())
+    }
+
+    fn update_page(&self, _idx: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn decommit_page(&self, _idx: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn commit_overwrite(&self, idx: usize) -> Result<UFrame> {
+        self.commit_page(idx)
+    }
+}
+
+/// The runtime state of an opened PCM device.
+///
+/// The pointers are in frames and wrap around at the boundary, as in Linux.
+struct Runtime {
+    state: PcmState,
+    /// The hardware parameters and the buffer, which exist unless the state is `Open`.
+    hw: Option<(HwParams, PcmBuffer)>,
+    /// The position that the device has played to.
+    hw_ptr: u64,
+    /// The position that the application has written to.
+    appl_ptr: u64,
+    avail_min: u64,
+    start_threshold: u64,
+    stop_threshold: u64,
+    boundary: u64,
+}
+
+impl Runtime {
+    fn new() -> Self {
+        Self {
+            state: PcmState::Open,
+            hw: None,
+            hw_ptr: 0,
+            appl_ptr: 0,
+            avail_min: 1,
+            start_threshold: 1,
+            stop_threshold: 0,
+            boundary: 0,
+        }
+    }
+
+    fn hw_params(&self) -> Result<&HwParams> {
+        match self.hw.as_ref() {
+            Some((hw_params, _)) => Ok(hw_params),
+            None => {
+                return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set")
+            }
+        }
+    }
+
+    fn buffer(&self) -> Result<&PcmBuffer> {
+        match self.hw.as_ref() {
+            Some((_, buffer)) => Ok(buffer),
+            None => {
+                return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set")
+            }
+        }
+    }
+
+    fn buffer_size(&self) -> u64 {
+        self.hw
+            .as_ref()
+            .map_or(0, |(hw_params, _)| hw_params.buffer_size())
+    }
+
+    /// Returns the number of the frames that can be written.
+    fn avail(&self) -> u64 {
+        if self.boundary == 0 {
+            return 0;
+        }
+        (self.hw_ptr + self.buffer_size() + self.boundary - self.appl_ptr) % self.boundary
+    }
+
+    /// Returns the number of the frames that are written but not played.
+    fn delay(&self) -> u64 {
+        self.buffer_size().saturating_sub(self.avail())
+    }
+
+    fn advance(&self, ptr: u64, frames: u64) -> u64 {
+        (ptr + frames) % self.boundary
+    }
+
+    /// Sets the hardware parameters and the buffer, with the software parameters reset to the
+    /// defaults.
+    fn set_hw(&mut self, hw_params: HwParams, buffer: PcmBuffer) {
+        let buffer_size = hw_params.buffer_size();
+
+        // Like Linux, the boundary is the largest multiple of the buffer size in the form of
+        // `buffer_size * 2^n` that does not overflow.
+        let mut boundary = buffer_size;
+        while boundary * 2 <= i64::MAX as u64 - buffer_size {
+            boundary *= 2;
+        }
+
+        self.state = PcmState::Setup;
+        self.hw = Some((hw_params, buffer));
+        self.hw_ptr = 0;
+        self.appl_ptr = 0;
+        self.avail_min = hw_params.period_size;
+        self.start_threshold = 1;
+        self.stop_threshold = buffer_size;
+        self.boundary = boundary;
+    }
+
+    /// Returns the error if the data cannot be transferred in the current state.
+    fn check_xfer_state(&self) -> Result<()> {
+        match self.state {
+            PcmState::Prepared | PcmState::Running => Ok(()),
+            PcmState::Xrun => return_errno_with_message!(Errno::EPIPE, "the PCM stream underruns"),
+            _ => return_errno_with_message!(Errno::EBADFD, "the PCM stream is not prepared"),
+        }
+    }
+}
+
+/// An opened playback PCM device.
+struct PcmFile {
+    device: Arc<PcmDev>,
+    /// The lock that serializes the operations, which may sleep and talk to the device.
+    op_lock: Mutex<()>,
+    /// The runtime state, which is also updated in the interrupt context.
+    runtime: SpinLock<Runtime>,
+    pollee: Pollee,
+}
+
+impl PcmFile {
+    fn stream(&self) -> &Arc<dyn PcmStream> {
+        self.device.stream()
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let runtime = self.runtime.disable_irq().lock();
+        match runtime.state {
+            PcmState::Prepared | PcmState::Running => {
+                if runtime.avail() >= runtime.avail_min {
+                    IoEvents::OUT
+                } else {
+                    IoEvents::empty()
+                }
+            }
+            PcmState::Draining => IoEvents::empty(),
+            _ => IoEvents::OUT | IoEvents::ERR,
+        }
+    }
+
+    fn get_info(&self, arg: usize) -> Result<()> {
+        let mut info = c_snd_pcm_info::new_zeroed();
+        info.device = self.device.index;
+        info.stream = SNDRV_PCM_STREAM_PLAYBACK;
+        info.card = self.device.card.index() as i32;
+        let name = self.device.card.name().as_bytes();
+        let len = name.len().min(info.id.len() - 1);
+        info.id[..len].copy_from_slice(&name[..len]);
+        info.name[..len].copy_from_slice(&name[..len]);
+        let subname = b"subdevice #0";
+        info.subname[..subname.len()].copy_from_slice(subname);
+        info.subdevices_count = 1;
+
+        current_userspace!().write_val(arg, &info)
+    }
+
+    fn refine_hw_params(&self, arg: usize) -> Result<()> {
+        let mut params: c_snd_pcm_hw_params = current_userspace!().read_val(arg)?;
+        refine_hw_params(self.stream().info(), &mut params)?;
+        current_userspace!().write_val(arg, &params)
+    }
+
+    fn set_hw_params(&self, arg: usize) -> Result<()> {
+        let mut params: c_snd_pcm_hw_params = current_userspace!().read_val(arg)?;
+
+        let _guard = self.op_lock.lock();
+        match self.runtime.disable_irq().lock().state {
+            PcmState::Open | PcmState::Setup | PcmState::Prepared => (),
+            _ => return_errno_with_message!(Errno::EBADFD, "the PCM stream is running"),
+        }
+
+        let hw_params = choose_hw_params(self.stream().info(), &params)?;
+        let buffer = PcmBuffer::new(hw_params.buffer_bytes())?;
+        self.stream().stop()?;
+        self.runtime.disable_irq().lock().set_hw(hw_params, buffer);
+        self.pollee.invalidate();
+
+        fill_hw_params(&mut params, &hw_params);
+        current_userspace!().write_val(arg, &params)
+    }
+
+    fn free_hw_params(&self) -> Result<()> {
+        let _guard = self.op_lock.lock();
+        match self.runtime.disable_irq().lock().state {
+            PcmState::Running | PcmState::Draining => {
+                return_errno_with_message!(Errno::EBADFD, "the PCM stream is running")
+            }
+            _ => (),
+        }
+
+        self.stream().stop()?;
+        *self.runtime.disable_irq().lock() = Runtime::new();
+        self.pollee.invalidate();
+        Ok(())
+    }
+
+    fn set_sw_params(&self, arg: usize) -> Result<()> {
+        let mut params: c_snd_pcm_sw_params = current_userspace!().read_val(arg)?;
+        if params.tstamp_type > SNDRV_PCM_TSTAMP_TYPE_LAST as u32 {
+            return_errno_with_message!(Errno::EINVAL, "the timestamp type is invalid");
+        }
+        if params.silence_threshold != 0 || params.silence_size != 0 {
+            return_errno_with_message!(Errno::EINVAL, "filling silence is not supported");
+        }
+
+        let _guard = self.op_lock.lock();
+        let mut runtime = self.runtime.disable_irq().lock();
+        if runtime.state == PcmState::Open {
+            return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set");
+        }
+        runtime.avail_min = params.avail_min.max(1);
+        runtime.start_threshold = params.start_threshold;
+        runtime.stop_threshold = params.stop_threshold;
+        params.boundary = runtime.boundary;
+        drop(runtime);
+        self.pollee.invalidate();
+
+        current_userspace!().write_val(arg, &params)
+    }
+
+    fn prepare(&self) -> Result<()> {
+        let _guard = self.op_lock.lock();
+        let (hw_params, buffer) = {
+            let runtime = self.runtime.disable_irq().lock();
+            match runtime.state {
+                PcmState::Open => {
+                    return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set")
+                }
+                PcmState::Running | PcmState::Draining => {
+                    return_errno_with_message!(Errno::EBUSY, "the PCM stream is running")
+                }
+                _ => (),
+            }
+            (*runtime.hw_params()?, runtime.buffer()?.clone())
+        };
+
+        self.stream()
+            .prepare(&hw_params.to_pcm_params(), &buffer.dma)?;
+
+        let mut runtime = self.runtime.disable_irq().lock();
+        runtime.state = PcmState::Prepared;
+        runtime.hw_ptr = 0;
+        runtime.appl_ptr = 0;
+        drop(runtime);
+        self.pollee.invalidate();
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        let _guard = self.op_lock.lock();
+        let mut runtime = self.runtime.disable_irq().lock();
+        match runtime.state {
+            PcmState::Prepared | PcmState::Running => (),
+            PcmState::Xrun => return_errno_with_message!(Errno::EPIPE, "the PCM stream underruns"),
+            _ => return_errno_with_message!(Errno::EBADFD, "the PCM stream is not prepared"),
+        }
+        runtime.appl_ptr = runtime.hw_ptr;
+        drop(runtime);
+        self.pollee.invalidate();
+        Ok(())
+    }
+
+    fn start(&self) -> Result<()> {
+        let _guard = self.op_lock.lock();
+        {
+            let runtime = self.runtime.disable_irq().lock();
+            if runtime.state != PcmState::Prepared {
+                return_errno_with_message!(Errno::EBADFD, "the PCM stream is not prepared");
+            }
+            if runtime.avail() >= runtime.buffer_size() && runtime.stop_threshold < runtime.boundary
+            {
+                return_errno_with_message!(Errno::EPIPE, "the PCM stream has no data");
+            }
+        }
+        self.do_start()
+    }
+
+    /// Starts the stream, which should be in the `Prepared` state.
+    ///
+    /// This method should be called with `op_lock` held.
+    fn do_start(&self) -> Result<()> {
+        self.runtime.disable_irq().lock().state = PcmState::Running;
+        if let Err(err) = self.stream().start() {
+            self.runtime.disable_irq().lock().state = PcmState::Xrun;
+            self.pollee.notify(IoEvents::ERR);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn drop_stream(&self) -> Result<()> {
+        let _guard = self.op_lock.lock();
+        match self.runtime.disable_irq().lock().state {
+            PcmState::Open => {
+                return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set")
+            }
+            PcmState::Setup => return Ok(()),
+            _ => (),
+        }
+
+        self.stream().stop()?;
+        self.runtime.disable_irq().lock().state = PcmState::Setup;
+        self.pollee.notify(IoEvents::OUT | IoEvents::ERR);
+        Ok(())
+    }
+
+    fn drain(&self) -> Result<()> {
+        {
+            let _guard = self.op_lock.lock();
+            let state = self.runtime.disable_irq().lock().state;
+            match state {
+                PcmState::Open => {
+                    return_errno_with_message!(Errno::EBADFD, "the hardware parameters are not set")
+                }
+                PcmState::Setup => return Ok(()),
+                PcmState::Xrun => {
+                    self.stream().stop()?;
+                    self.runtime.disable_irq().lock().state = PcmState::Setup;
+                    return Ok(());
+                }
+                PcmState::Prepared => {
+                    let runtime = self.runtime.disable_irq().lock();
+                    let has_data = runtime.avail() < runtime.buffer_size();
+                    drop(runtime);
+
+                    if !has_data {
+                        self.runtime.disable_irq().lock().state = PcmState::Setup;
+                        return Ok(());
+                    }
+                    self.do_start()?;
+                }
+                PcmState::Running | PcmState::Draining => (),
+            }
+
+            // The remaining space is filled with silence, so that the device will not play the
+            // stale data in the periods after the last one with the data.
+            let mut runtime = self.runtime.disable_irq().lock();
+            if runtime.state == PcmState::Running {
+                fill_silence(&runtime)?;
+                runtime.state = PcmState::Draining;
+            }
+        }
+
+        // The operations are not blocked while waiting, so the stream can be dropped meanwhile.
+        self.wait_events(IoEvents::OUT | IoEvents::ERR, None, || {
+            if self.runtime.disable_irq().lock().state == PcmState::Draining {
+                return_errno_with_message!(Errno::EAGAIN, "the PCM stream is draining");
+            }
+            Ok(())
+        })?;
+
+        // The stream may also run out of the data before it is drained, e.g., if the data is
+        // too little to start the stream.
+        let _guard = self.op_lock.lock();
+        let state = self.runtime.disable_irq().lock().state;
+        if state == PcmState::Setup || state == PcmState::Xrun {
+            self.stream().stop()?;
+            self.runtime.disable_irq().lock().state = PcmState::Setup;
+        }
+        Ok(())
+    }
+
+    fn get_delay(&self, arg: usize) -> Result<()> {
+        let delay = {
+            let runtime = self.runtime.disable_irq().lock();
+            if runtime.state != PcmState::Draining {
+                runtime.check_xfer_state()?;
+            }
+            runtime.delay() as i64
+        };
+        current_userspace!().write_val(arg, &delay)
+    }
+
+    fn hwsync(&self) -> Result<()> {
+        let runtime = self.runtime.disable_irq().lock();
+        if runtime.state != PcmState::Draining {
+            runtime.check_xfer_state()?;
+        }
+        // The hardware pointer is updated when the periods are played, so there is nothing to
+        // synchronize.
+        Ok(())
+    }
+
+    fn sync_ptr(&self, arg: usize) -> Result<()> {
+        let mut sync_ptr: c_snd_pcm_sync_ptr = current_userspace!().read_val(arg)?;
+
+        let _guard = self.op_lock.lock();
+        let mut runtime = self.runtime.disable_irq().lock();
+        if sync_ptr.flags & SNDRV_PCM_SYNC_PTR_APPL == 0 && runtime.boundary > 0 {
+            let appl_ptr = sync_ptr.control.appl_ptr;
+            let new_avail = (runtime.hw_ptr + runtime.buffer_size() + runtime.boundary
+                - appl_ptr % runtime.boundary)
+                % runtime.boundary;
+            if appl_ptr >= runtime.boundary || new_avail > runtime.buffer_size() {
+                return_errno_with_message!(Errno::EINVAL, "the application pointer is invalid");
+            }
+            runtime.appl_ptr = appl_ptr;
+        }
+        if sync_ptr.flags & SNDRV_PCM_SYNC_PTR_AVAIL_MIN == 0 {
+            runtime.avail_min = sync_ptr.control.avail_min.max(1);
+        }
+
+        sync_ptr.status = c_snd_pcm_mmap_status::new_zeroed();
+        sync_ptr.status.state = runtime.state as i32;
+        sync_ptr.status.hw_ptr = runtime.hw_ptr;
+        sync_ptr.control.appl_ptr = runtime.appl_ptr;
+        sync_ptr.control.avail_min = runtime.avail_min;
+        drop(runtime);
+        self.pollee.invalidate();
+
+        current_userspace!().write_val(arg, &sync_ptr)
+    }
+
+    fn writei_frames(&self, arg: usize) -> Result<()> {
+        let mut xferi: c_snd_xferi = current_userspace!().read_val(arg)?;
+
+        let frame_bytes = self.runtime.disable_irq().lock().hw_params()?.frame_bytes();
+        let len = (xferi.frames as usize)
+            .checked_mul(frame_bytes)
+            .ok_or(Error::with_message(Errno::EINVAL, "too many frames"))?;
+        let user_space = current_userspace!();
+        let mut reader = user_space.reader(xferi.buf as Vaddr, len)?;
+
+        xferi.result = self.write_frames(&mut reader)? as i64;
+        current_userspace!().write_val(arg, &xferi)
+    }
+
+    /// Writes the frames in the reader to the buffer, and returns the number of the frames that
+    /// are written.
+    ///
+    /// This method blocks until all the frames are written, or the stream stops.
+    fn write_frames(&self, reader: &mut VmReader) -> Result<u64> {
+        let _guard = self.op_lock.lock();
+
+        let frame_bytes = {
+            let runtime = self.runtime.disable_irq().lock();
+            let hw_params = runtime.hw_params()?;
+            if hw_params.access != SNDRV_PCM_ACCESS_RW_INTERLEAVED {
+                return_errno_with_message!(Errno::EINVAL, "the access type is not read/write");
+            }
+            hw_params.frame_bytes()
+        };
+        let frames = (reader.remain() / frame_bytes) as u64;
+
+        let mut written = 0;
+        while written < frames {
+            let res = self.wait_events(IoEvents::OUT, None, || {
+                let runtime = self.runtime.disable_irq().lock();
+                runtime.check_xfer_state()?;
+                if runtime.avail() == 0 {
+                    return_errno_with_message!(Errno::EAGAIN, "the PCM buffer is full");
+                }
+                Ok(())
+            });
+            if let Err(err) = res {
+                if written > 0 {
+                    break;
+                }
+                return Err(err);
+            }
+
+            let (buffer, offset, count) = {
+                let runtime = self.runtime.disable_irq().lock();
+                let buffer_size = runtime.buffer_size();
+                let offset = runtime.appl_ptr % buffer_size;
+                // The frames are copied up to the end of the buffer at a time.
+                let count = runtime
+                    .avail()
+                    .min(frames - written)
+                    .min(buffer_size - offset);
+                (runtime.buffer()?.clone(), offset, count)
+            };
+
+            let mut writer = buffer.dma.writer()?;
+            writer
+                .skip(offset as usize * frame_bytes)
+                .limit(count as usize * frame_bytes);
+            if let Err(err) = reader.read_fallible(&mut writer) {
+                if written > 0 {
+                    break;
+                }
+                return Err(err.into());
+            }
+            written += count;
+
+            let should_start = {
+                let mut runtime = self.runtime.disable_irq().lock();
+                runtime.appl_ptr = runtime.advance(runtime.appl_ptr, count);
+                runtime.state == PcmState::Prepared && runtime.delay() >= runtime.start_threshold
+            };
+            self.pollee.invalidate();
+            if should_start {
+                self.do_start()?;
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+impl PcmListener for PcmFile {
+    fn on_period_elapsed(&self) -> bool {
+        let mut runtime = self.runtime.lock();
+        let Ok(hw_params) = runtime.hw_params() else {
+            return false;
+        };
+        let period_size = hw_params.period_size;
+
+        let keep_running = match runtime.state {
+            PcmState::Running => {
+                runtime.hw_ptr = runtime.advance(runtime.hw_ptr, period_size);
+                if runtime.avail() >= runtime.stop_threshold {
+                    runtime.state = PcmState::Xrun;
+                    false
+                } else {
+                    true
+                }
+            }
+            PcmState::Draining => {
+                runtime.hw_ptr = runtime.advance(runtime.hw_ptr, period_size);
+                // The stream is drained when the hardware pointer passes the application
+                // pointer, in which case the available frames exceed the buffer.
+                if runtime.avail() >= runtime.buffer_size() {
+                    runtime.state = PcmState::Setup;
+                    false
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        };
+        drop(runtime);
+
+        self.pollee.notify(IoEvents::OUT | IoEvents::ERR);
+        keep_running
+    }
+}
+
+impl Pollable for PcmFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileIo for PcmFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the playback PCM device cannot be read");
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let frame_bytes = self.runtime.disable_irq().lock().hw_params()?.frame_bytes();
+        if reader.remain() % frame_bytes != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the data is not aligned to the frames");
+        }
+        let written = self.write_frames(reader)?;
+        Ok(written as usize * frame_bytes)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::SNDRV_PCM_IOCTL_PVERSION => {
+                current_userspace!().write_val(arg, &SNDRV_PCM_VERSION)?
+            }
+            IoctlCmd::SNDRV_PCM_IOCTL_INFO => self.get_info(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_TSTAMP | IoctlCmd::SNDRV_PCM_IOCTL_USER_PVERSION => {
+                // The timestamps are not reported, and the protocol of the user space does not
+                // matter, so the values are only checked to be readable.
+                current_userspace!().read_val::<i32>(arg)?;
+            }
+            IoctlCmd::SNDRV_PCM_IOCTL_TTSTAMP => {
+                let tstamp_type: i32 = current_userspace!().read_val(arg)?;
+                if !(0..=SNDRV_PCM_TSTAMP_TYPE_LAST).contains(&tstamp_type) {
+                    return_errno_with_message!(Errno::EINVAL, "the timestamp type is invalid");
+                }
+            }
+            IoctlCmd::SNDRV_PCM_IOCTL_HW_REFINE => self.refine_hw_params(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_HW_PARAMS => self.set_hw_params(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_HW_FREE => self.free_hw_params()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_SW_PARAMS => self.set_sw_params(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_DELAY => self.get_delay(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_HWSYNC => self.hwsync()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_SYNC_PTR => self.sync_ptr(arg)?,
+            IoctlCmd::SNDRV_PCM_IOCTL_PREPARE => self.prepare()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_RESET => self.reset()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_START => self.start()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_DROP => self.drop_stream()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_DRAIN => self.drain()?,
+            IoctlCmd::SNDRV_PCM_IOCTL_WRITEI_FRAMES => self.writei_frames(arg)?,
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        Ok(0)
+    }
+
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        match offset {
+            SNDRV_PCM_MMAP_OFFSET_DATA => (),
+            SNDRV_PCM_MMAP_OFFSET_STATUS | SNDRV_PCM_MMAP_OFFSET_CONTROL => {
+                return_errno_with_message!(
+                    Errno::ENXIO,
+                    "the status and the control records cannot be mapped"
+                );
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the mmap offset is invalid"),
+        }
+
+        let runtime = self.runtime.disable_irq().lock();
+        if runtime.hw_params()?.access != SNDRV_PCM_ACCESS_MMAP_INTERLEAVED {
+            return_errno_with_message!(Errno::EINVAL, "the access type is not mmap");
+        }
+        let vmo = runtime.buffer()?.vmo.clone();
+        drop(runtime);
+
+        Ok((vmo.dup()?, 0))
+    }
+}
+
+impl Drop for PcmFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.stream().stop() {
+            warn!("failed to stop the PCM stream: {:?}", err);
+        }
+        self.device.is_opened.store(false, Ordering::Release);
+    }
+}
+
+/// Fills the available space of the buffer with silence.
+fn fill_silence(runtime: &Runtime) -> Result<()> {
+    let hw_params = runtime.hw_params()?;
+    let buffer = runtime.buffer()?;
+    let frame_bytes = hw_params.frame_bytes();
+    let buffer_size = hw_params.buffer_size();
+
+    let mut ptr = runtime.appl_ptr;
+    let mut remain = runtime.avail();
+    while remain > 0 {
+        let offset = ptr % buffer_size;
+        let count = remain.min(buffer_size - offset);
+
+        let mut writer = buffer.dma.writer()?;
+        writer
+            .skip(offset as usize * frame_bytes)
+            .limit(count as usize * frame_bytes);
+        fill_samples(&mut writer, hw_params.format);
+
+        ptr = runtime.advance(ptr, count);
+        remain -= count;
+    }
+    Ok(())
+}
+
+fn fill_samples(writer: &mut VmWriter<Infallible>, format: PcmFormat) {
+    let silence = format.silence();
+    match format.physical_width() {
+        8 => writer.fill(silence as u8),
+        16 => writer.fill(silence as u16),
+        _ => writer.fill(silence),
+    };
+}
+
+/// Chooses the hardware parameters in the configuration space of `params`.
+///
+/// Like Linux, the smallest values are chosen, except that the buffer is as large as possible.
+fn choose_hw_params(info: &PcmInfo, params: &c_snd_pcm_hw_params) -> Result<HwParams> {
+    let masks = &params.masks;
+    let intervals = &params.intervals;
+    let interval = |param: usize| &intervals[param - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL];
+    let no_config = || Error::with_message(Errno::EINVAL, "no configuration is supported");
+
+    let access = [
+        SNDRV_PCM_ACCESS_MMAP_INTERLEAVED,
+        SNDRV_PCM_ACCESS_RW_INTERLEAVED,
+    ]
+    .into_iter()
+    .find(|access| masks[SNDRV_PCM_HW_PARAM_ACCESS].test(*access))
+    .ok_or_else(no_config)?;
+    if !masks[SNDRV_PCM_HW_PARAM_SUBFORMAT].test(SNDRV_PCM_SUBFORMAT_STD) {
+        return Err(no_config());
+    }
+
+    let sample_bits = interval(SNDRV_PCM_HW_PARAM_SAMPLE_BITS).range(false);
+    let format = info
+        .formats
+        .iter()
+        .copied()
+        .filter(|format| masks[SNDRV_PCM_HW_PARAM_FORMAT].test(*format as u32))
+        .filter(|format| contains(sample_bits, format.physical_width() as u64))
+        .min_by_key(|format| *format as u32)
+        .ok_or_else(no_config)?;
+    let sample_bits = format.physical_width() as u64;
+
+    let channels = intersect(
+        interval(SNDRV_PCM_HW_PARAM_CHANNELS).range(false),
+        div_range(
+            interval(SNDRV_PCM_HW_PARAM_FRAME_BITS).range(false),
+            sample_bits,
+        ),
+    );
+    let channels = info
+        .channels
+        .clone()
+        .find(|value| contains(channels, *value as u64))
+        .ok_or_else(no_config)?;
+    let frame_bytes = sample_bits / 8 * channels as u64;
+
+    let rate_range = interval(SNDRV_PCM_HW_PARAM_RATE).range(false);
+    let rate = info
+        .rates
+        .iter()
+        .copied()
+        .find(|rate| contains(rate_range, *rate as u64))
+        .ok_or_else(no_config)?;
+
+    let periods = intersect(
+        interval(SNDRV_PCM_HW_PARAM_PERIODS).range(false),
+        Some((*info.periods.start() as u64, *info.periods.end() as u64)),
+    )
+    .ok_or_else(no_config)?;
+    let period_size = [
+        interval(SNDRV_PCM_HW_PARAM_PERIOD_SIZE).range(false),
+        div_range(
+            interval(SNDRV_PCM_HW_PARAM_PERIOD_BYTES).range(false),
+            frame_bytes,
+        ),
+        time_to_frames(interval(SNDRV_PCM_HW_PARAM_PERIOD_TIME).range(true), rate),
+        div_range(
+            Some((PERIOD_BYTES_MIN as u64, (info.buffer_bytes_max / 2) as u64)),
+            frame_bytes,
+        ),
+    ]
+    .into_iter()
+    .reduce(intersect)
+    .unwrap()
+    .ok_or_else(no_config)?;
+    let buffer_size = [
+        interval(SNDRV_PCM_HW_PARAM_BUFFER_SIZE).range(false),
+        div_range(
+            interval(SNDRV_PCM_HW_PARAM_BUFFER_BYTES).range(false),
+            frame_bytes,
+        ),
+        time_to_frames(interval(SNDRV_PCM_HW_PARAM_BUFFER_TIME).range(true), rate),
+        div_range(Some((0, info.buffer_bytes_max as u64)), frame_bytes),
+    ]
+    .into_iter()
+    .reduce(intersect)
+    .unwrap()
+    .ok_or_else(no_config)?;
+
+    // Choose the smallest period size with which some number of periods fits in the buffer, and
+    // then the largest number of periods.
+    let period_size = (periods.0..=periods.1)
+        .filter_map(|periods| {
+            let min = period_size.0.max(buffer_size.0.div_ceil(periods));
+            let max = period_size.1.min(buffer_size.1 / periods);
+            (min <= max).then_some(min)
+        })
+        .min()
+        .ok_or_else(no_config)?;
+    let periods = (periods.0..=periods.1)
+        .rev()
+        .find(|periods| contains(Some(buffer_size), periods * period_size))
+        .unwrap();
+
+    Ok(HwParams {
+        access,
+        format,
+        channels,
+        rate,
+        period_size,
+        periods,
+    })
+}
+
+/// Fills the chosen hardware parameters in `params`.
+fn fill_hw_params(params: &mut c_snd_pcm_hw_params, hw_params: &HwParams) {
+    let frame_bytes = hw_params.frame_bytes() as u64;
+    let sample_bits = hw_params.format.physical_width();
+
+    params.masks[SNDRV_PCM_HW_PARAM_ACCESS] = c_snd_mask::single(hw_params.access);
+    params.masks[SNDRV_PCM_HW_PARAM_FORMAT] = c_snd_mask::single(hw_params.format as u32);
+    params.masks[SNDRV_PCM_HW_PARAM_SUBFORMAT] = c_snd_mask::single(SNDRV_PCM_SUBFORMAT_STD);
+
+    let values = [
+        (SNDRV_PCM_HW_PARAM_SAMPLE_BITS, sample_bits as u64),
+        (
+            SNDRV_PCM_HW_PARAM_FRAME_BITS,
+            (sample_bits * hw_params.channels) as u64,
+        ),
+        (SNDRV_PCM_HW_PARAM_CHANNELS, hw_params.channels as u64),
+        (SNDRV_PCM_HW_PARAM_RATE, hw_params.rate as u64),
+        (SNDRV_PCM_HW_PARAM_PERIOD_SIZE, hw_params.period_size),
+        (
+            SNDRV_PCM_HW_PARAM_PERIOD_BYTES,
+            hw_params.period_size * frame_bytes,
+        ),
+        (SNDRV_PCM_HW_PARAM_PERIODS, hw_params.periods),
+        (SNDRV_PCM_HW_PARAM_BUFFER_SIZE, hw_params.buffer_size()),
+        (
+            SNDRV_PCM_HW_PARAM_BUFFER_BYTES,
+            hw_params.buffer_size() * frame_bytes,
+        ),
+        (SNDRV_PCM_HW_PARAM_TICK_TIME, 0),
+    ];
+    for (param, value) in values {
+        params.intervals[param - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL] =
+            c_snd_interval::single(value as u32);
+    }
+    params.intervals[SNDRV_PCM_HW_PARAM_PERIOD_TIME - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL] =
+        c_snd_interval::time(hw_params.period_size, hw_params.rate);
+    params.intervals[SNDRV_PCM_HW_PARAM_BUFFER_TIME - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL] =
+        c_snd_interval::time(hw_params.buffer_size(), hw_params.rate);
+
+    params.rmask = 0;
+    params.cmask = ALL_PARAMS_MASK;
+    params.info = SNDRV_PCM_INFO_MMAP | SNDRV_PCM_INFO_MMAP_VALID | SNDRV_PCM_INFO_INTERLEAVED;
+    params.msbits = hw_params.format.width();
+    params.rate_num = hw_params.rate;
+    params.rate_den = 1;
+    params.fifo_size = 0;
+}
+
+/// Narrows the configuration space of `params` to the supported one.
+///
+/// Only the parameters that are directly limited by the stream are narrowed. The dependencies
+/// between the parameters are resolved when they are chosen by `SNDRV_PCM_IOCTL_HW_PARAMS`.
+fn refine_hw_params(info: &PcmInfo, params: &mut c_snd_pcm_hw_params) -> Result<()> {
+    let masks = &mut params.masks;
+    masks[SNDRV_PCM_HW_PARAM_ACCESS].retain(|access| {
+        access == SNDRV_PCM_ACCESS_MMAP_INTERLEAVED || access == SNDRV_PCM_ACCESS_RW_INTERLEAVED
+    })?;
+    masks[SNDRV_PCM_HW_PARAM_FORMAT].retain(|format| {
+        info.formats
+            .iter()
+            .any(|supported| *supported as u32 == format)
+    })?;
+    masks[SNDRV_PCM_HW_PARAM_SUBFORMAT].retain(|subformat| subformat == SNDRV_PCM_SUBFORMAT_STD)?;
+
+    let format_mask = masks[SNDRV_PCM_HW_PARAM_FORMAT];
+    let sample_bits = info
+        .formats
+        .iter()
+        .filter(|format| format_mask.test(**format as u32))
+        .map(|format| format.physical_width());
+    let sample_bits_min = sample_bits.clone().min().unwrap();
+    let sample_bits_max = sample_bits.max().unwrap();
+
+    let intervals = &mut params.intervals;
+    let mut refine = |param: usize, min: u64, max: u64| {
+        intervals[param - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL].refine(min, max)
+    };
+    refine(
+        SNDRV_PCM_HW_PARAM_SAMPLE_BITS,
+        sample_bits_min as u64,
+        sample_bits_max as u64,
+    )?;
+    refine(
+        SNDRV_PCM_HW_PARAM_CHANNELS,
+        *info.channels.start() as u64,
+        *info.channels.end() as u64,
+    )?;
+    refine(
+        SNDRV_PCM_HW_PARAM_FRAME_BITS,
+        (sample_bits_min * info.channels.start()) as u64,
+        (sample_bits_max * info.channels.end()) as u64,
+    )?;
+
+    let rate_range =
+        intervals[SNDRV_PCM_HW_PARAM_RATE - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL].range(false);
+    let mut rates = info
+        .rates
+        .iter()
+        .map(|rate| *rate as u64)
+        .filter(|rate| contains(rate_range, *rate));
+    let rate_min = rates.next().unwrap_or(u64::MAX);
+    let rate_max = rates.last().unwrap_or(rate_min);
+    intervals[SNDRV_PCM_HW_PARAM_RATE - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL]
+        .refine(rate_min, rate_max)?;
+
+    let mut refine = |param: usize, min: u64, max: u64| {
+        intervals[param - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL].refine(min, max)
+    };
+    refine(
+        SNDRV_PCM_HW_PARAM_PERIODS,
+        *info.periods.start() as u64,
+        *info.periods.end() as u64,
+    )?;
+    refine(
+        SNDRV_PCM_HW_PARAM_PERIOD_BYTES,
+        PERIOD_BYTES_MIN as u64,
+        (info.buffer_bytes_max / 2) as u64,
+    )?;
+    refine(
+        SNDRV_PCM_HW_PARAM_BUFFER_BYTES,
+        (PERIOD_BYTES_MIN * 2) as u64,
+        info.buffer_bytes_max as u64,
+    )?;
+
+    params.rmask = 0;
+    params.cmask = ALL_PARAMS_MASK;
+    params.info = SNDRV_PCM_INFO_MMAP | SNDRV_PCM_INFO_MMAP_VALID | SNDRV_PCM_INFO_INTERLEAVED;
+    params.fifo_size = 0;
+    Ok(())
+}
+
+/// An inclusive range of values, which is `None` if it is empty.
+type ValueRange = Option<(u64, u64)>;
+
+fn contains(range: ValueRange, value: u64) -> bool {
+    range.is_some_and(|(min, max)| min <= value && value <= max)
+}
+
+fn intersect(a: ValueRange, b: ValueRange) -> ValueRange {
+    let (a_min, a_max) = a?;
+    let (b_min, b_max) = b?;
+    let (min, max) = (a_min.max(b_min), a_max.min(b_max));
+    (min <= max).then_some((min, max))
+}
+
+/// Divides the range of the products by the factor, e.g., to convert bytes to frames.
+fn div_range(range: ValueRange, factor: u64) -> ValueRange {
+    let (min, max) = range?;
+    intersect(
+        Some((min.div_ceil(factor), max / factor)),
+        Some((0, u64::MAX)),
+    )
+}
+
+/// Converts the range of the time in microseconds to the range of the frames.
+fn time_to_frames(range: ValueRange, rate: u32) -> ValueRange {
+    let (min, max) = range?;
+    let rate = rate as u64;
+    intersect(
+        Some((
+            (min * rate).div_ceil(MICROS_PER_SEC),
+            max * rate / MICROS_PER_SEC,
+        )),
+        Some((0, u64::MAX)),
+    )
+}
+
+impl From<SoundError> for Error {
+    fn from(err: SoundError) -> Self {
+        match err {
+            SoundError::NotSupported => {
+                Error::with_message(Errno::EINVAL, "the PCM parameters are not supported")
+            }
+            SoundError::InvalidArgs => {
+                Error::with_message(Errno::EINVAL, "the PCM operation is invalid")
+            }
+            SoundError::Io => Error::with_message(Errno::EIO, "the PCM operation fails"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/sound/asound.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_info {
+    device: u32,
+    subdevice: u32,
+    stream: i32,
+    card: i32,
+    id: [u8; 64],
+    name: [u8; 80],
+    subname: [u8; 32],
+    dev_class: i32,
+    dev_subclass: i32,
+    subdevices_count: u32,
+    subdevices_avail: u32,
+    sync: [u8; 16],
+    reserved: [u8; 64],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_mask {
+    bits: [u32; 8],
+}
+
+impl c_snd_mask {
+    fn single(bit: u32) -> Self {
+        let mut mask = Self::new_zeroed();
+        mask.bits[(bit / 32) as usize] = 1 << (bit % 32);
+        mask
+    }
+
+    fn test(&self, bit: u32) -> bool {
+        bit < 256 && self.bits[(bit / 32) as usize] & (1 << (bit % 32)) != 0
+    }
+
+    /// Keeps the bits that satisfy `f`, and fails if no bits are left.
+    fn retain(&mut self, mut f: impl FnMut(u32) -> bool) -> Result<()> {
+        for bit in 0..256 {
+            if self.test(bit) && !f(bit) {
+                self.bits[(bit / 32) as usize] &= !(1 << (bit % 32));
+            }
+        }
+        if self.bits.iter().all(|bits| *bits == 0) {
+            return_errno_with_message!(Errno::EINVAL, "no configuration is supported");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_interval {
+    min: u32,
+    max: u32,
+    /// The bit fields of `openmin`, `openmax`, `integer`, and `empty`.
+    flags: u32,
+}
+
+impl c_snd_interval {
+    fn single(value: u32) -> Self {
+        Self {
+            min: value,
+            max: value,
+            flags: INTERVAL_INTEGER,
+        }
+    }
+
+    /// Returns the interval of the time in microseconds of the frames at the rate.
+    fn time(frames: u64, rate: u32) -> Self {
+        let time = frames * MICROS_PER_SEC;
+        let rate = rate as u64;
+        if time % rate == 0 {
+            Self::single((time / rate) as u32)
+        } else {
+            Self {
+                min: (time / rate) as u32,
+                max: (time / rate + 1) as u32,
+                flags: INTERVAL_OPENMIN | INTERVAL_OPENMAX,
+            }
+        }
+    }
+
+    /// Returns the range of the integers in the interval.
+    ///
+    /// The open bounds are ignored if `is_real` is true, since the interval of real numbers
+    /// (e.g., the time) may be open at the both ends of an integer.
+    fn range(&self, is_real: bool) -> ValueRange {
+        if self.flags & INTERVAL_EMPTY != 0 {
+            return None;
+        }
+
+        let mut min = self.min as u64;
+        let mut max = self.max as u64;
+        if !is_real && self.flags & INTERVAL_OPENMIN != 0 {
+            min += 1;
+        }
+        if !is_real && self.flags & INTERVAL_OPENMAX != 0 {
+            max = max.checked_sub(1)?;
+        }
+        (min <= max).then_some((min, max))
+    }
+
+    /// Narrows the interval to `min..=max`, and fails if it becomes empty.
+    fn refine(&mut self, min: u64, max: u64) -> Result<()> {
+        let min = min.min(u32::MAX as u64) as u32;
+        let max = max.min(u32::MAX as u64) as u32;
+        if self.min < min {
+            self.min = min;
+            self.flags &= !INTERVAL_OPENMIN;
+        }
+        if self.max > max {
+            self.max = max;
+            self.flags &= !INTERVAL_OPENMAX;
+        }
+
+        if self.range(false).is_none() {
+            self.flags |= INTERVAL_EMPTY;
+            return_errno_with_message!(Errno::EINVAL, "no configuration is supported");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_hw_params {
+    flags: u32,
+    masks: [c_snd_mask; 3],
+    mres: [c_snd_mask; 5],
+    intervals: [c_snd_interval; 12],
+    ires: [c_snd_interval; 9],
+    rmask: u32,
+    cmask: u32,
+    info: u32,
+    msbits: u32,
+    rate_num: u32,
+    rate_den: u32,
+    fifo_size: u64,
+    reserved: [u8; 64],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_sw_params {
+    tstamp_mode: i32,
+    period_step: u32,
+    sleep_min: u32,
+    _pad: u32,
+    avail_min: u64,
+    xfer_align: u64,
+    start_threshold: u64,
+    stop_threshold: u64,
+    silence_threshold: u64,
+    silence_size: u64,
+    boundary: u64,
+    proto: u32,
+    tstamp_type: u32,
+    reserved: [u8; 56],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_mmap_status {
+    state: i32,
+    _pad1: i32,
+    hw_ptr: u64,
+    tstamp: [i64; 2],
+    suspended_state: i32,
+    _pad3: i32,
+    audio_tstamp: [i64; 2],
+    /// The padding of the union in `struct snd_pcm_sync_ptr`.
+    _reserved: [u8; 8],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_mmap_control {
+    appl_ptr: u64,
+    avail_min: u64,
+    /// The padding of the union in `struct snd_pcm_sync_ptr`.
+    _reserved: [u8; 48],
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_pcm_sync_ptr {
+    flags: u32,
+    _pad: u32,
+    status: c_snd_pcm_mmap_status,
+    control: c_snd_pcm_mmap_control,
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_snd_xferi {
+    result: i64,
+    buf: u64,
+    frames: u64,
+}
@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SPI device interface (`/dev/spidevB.C`), which gives userspace access to the SPI devices
+//! driven by the `spidev` driver.
+//!
+//! Reference: <https://docs.kernel.org/spi/spidev.html>
+
+use core::time::Duration;
+
+use aster_spi::{SpiDevice, SpiDriver, SpiError, SpiMessage, SpiMode, SpiTransfer};
+use ostd::task::Task;
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the SPI devices, which is the same as Linux.
+pub(super) const SPIDEV_MAJOR: u32 = 153;
+
+/// The maximum number of bytes in a message, which is the default of Linux.
+const BUF_SIZE: usize = 4096;
+
+/// The names of the devices that can be driven by `spidev`.
+///
+/// Besides the legacy name, these are the devices for which Linux binds `spidev` in the device
+/// tree.
+const SPIDEV_ID_TABLE: &[&str] = &[
+    "spidev",
+    "cisco,spi-petra",
+    "lineartechnology,ltc2488",
+    "lwn,bk4",
+    "menlo,m53cpld",
+    "micron,spi-authenta",
+    "rohm,dh2228fv",
+    "semtech,sx1301",
+];
+
+/// The devices driven by `spidev`, which are indexed by their minor device numbers.
+static SPIDEVS: Mutex<Vec<Arc<SpiDevice>>> = Mutex::new(Vec::new());
+
+pub(super) fn init() -> Result<()> {
+    // The device nodes are added when the devices are probed.
+    aster_spi::register_driver(Arc::new(SpidevDriver));
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = SPIDEVS.lock().get(minor as usize).cloned() else {
+        return_errno_with_message!(Errno::ENODEV, "the SPI device does not exist");
+    };
+    Ok(Arc::new(SpidevDev { minor, device }))
+}
+
+#[derive(Debug)]
+struct SpidevDriver;
+
+impl SpiDriver for SpidevDriver {
+    fn id_table(&self) -> &[&str] {
+        SPIDEV_ID_TABLE
+    }
+
+    fn probe(&self, device: &Arc<SpiDevice>) -> core::result::Result<(), SpiError> {
+        let mut spidevs = SPIDEVS.lock();
+
+        let minor = spidevs.len() as u32;
+        let name = format!("spidev{}.{}", device.bus_nr(), device.chip_select());
+        let spidev = Arc::new(SpidevDev {
+            minor,
+            device: device.clone(),
+        });
+        if let Err(err) = add_node(spidev, &name, "spidev") {
+            warn!("failed to add the device node of {}: {:?}", name, err);
+            return Err(SpiError::Io);
+        }
+
+        spidevs.push(device.clone());
+        Ok(())
+    }
+}
+
+struct SpidevDev {
+    minor: u32,
+    device: Arc<SpiDevice>,
+}
+
+impl Device for SpidevDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(SPIDEV_MAJOR, self.minor)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(SpidevFile {
+            device: self.device.clone(),
+        })))
+    }
+}
+
+impl Pollable for SpidevDev {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for SpidevDev {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the SPI device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the SPI device is not opened");
+    }
+}
+
+/// An opened `/dev/spidevB.C`.
+///
+/// The configuration set by the ioctls belongs to the device, so it is shared by all the opened
+/// files of the device.
+struct SpidevFile {
+    device: Arc<SpiDevice>,
+}
+
+impl SpidevFile {
+    fn set_mode(&self, mode: u32) -> Result<()> {
+        let Some(mode) = SpiMode::from_bits(mode) else {
+            return_errno_with_message!(Errno::EINVAL, "the SPI mode is invalid");
+        };
+        let config = self.device.config();
+        self.setup(mode, config.bits_per_word, config.max_speed_hz)
+    }
+
+    fn set_lsb_first(&self, is_lsb_first: bool) -> Result<()> {
+        let config = self.device.config();
+        let mut mode = config.mode;
+        mode.set(SpiMode::LSB_FIRST, is_lsb_first);
+        self.setup(mode, config.bits_per_word, config.max_speed_hz)
+    }
+
+    fn set_bits_per_word(&self, bits_per_word: u8) -> Result<()> {
+        let config = self.device.config();
+        self.setup(config.mode, bits_per_word, config.max_speed_hz)
+    }
+
+    fn set_max_speed_hz(&self, max_speed_hz: u32) -> Result<()> {
+        let config = self.device.config();
+        self.setup(config.mode, config.bits_per_word, max_speed_hz)
+    }
+
+    fn setup(&self, mode: SpiMode, bits_per_word: u8, max_speed_hz: u32) -> Result<()> {
+        self.device
+            .setup(mode, bits_per_word, max_speed_hz)
+            .map_err(|_| {
+                Error::with_message(Errno::EINVAL, "the SPI configuration is not supported")
+            })
+    }
+
+    fn transfer_message(&self, arg: Vaddr, nr_transfers: usize) -> Result<i32> {
+        let current_task = Task::current().unwrap();
+        let user_space = CurrentUserSpace::new(&current_task);
+
+        let mut c_transfers = Vec::with_capacity(nr_transfers);
+        let mut transfers = Vec::with_capacity(nr_transfers);
+        let mut total_len = 0;
+        for i in 0..nr_transfers {
+            let c_transfer: c_spi_ioc_transfer =
+                user_space.read_val(arg + i * size_of::<c_spi_ioc_transfer>())?;
+            if c_transfer.tx_nbits > 1 || c_transfer.rx_nbits > 1 {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the dual, quad, and octal transfers are not supported"
+                );
+            }
+
+            let len = c_transfer.len as usize;
+            total_len += len;
+            if total_len > BUF_SIZE {
+                return_errno_with_message!(Errno::EMSGSIZE, "the SPI message is too long");
+            }
+
+            let tx_buf = if c_transfer.tx_buf != 0 {
+                let mut tx_buf = vec![0u8; len];
+                user_space.read_bytes(
+                    c_transfer.tx_buf as Vaddr,
+                    &mut VmWriter::from(tx_buf.as_mut_slice()),
+                )?;
+                Some(tx_buf)
+            } else {
+                None
+            };
+            let rx_buf = (c_transfer.rx_buf != 0).then(|| vec![0u8; len]);
+
+            transfers.push(SpiTransfer {
+                tx_buf,
+                rx_buf,
+                len,
+                speed_hz: c_transfer.speed_hz,
+                bits_per_word: c_transfer.bits_per_word,
+                cs_change: c_transfer.cs_change != 0,
+                delay: Duration::from_micros(c_transfer.delay_usecs as u64),
+            });
+            c_transfers.push(c_transfer);
+        }
+
+        let mut msg = SpiMessage::new(transfers);
+        self.device.sync(&mut msg)?;
+
+        for (c_transfer, transfer) in c_transfers.iter().zip(msg.transfers.iter()) {
+            if let Some(rx_buf) = transfer.rx_buf.as_ref() {
+                user_space.write_bytes(
+                    c_transfer.rx_buf as Vaddr,
+                    &mut VmReader::from(rx_buf.as_slice()),
+                )?;
+            }
+        }
+
+        Ok(msg.actual_length as i32)
+    }
+}
+
+impl Pollable for SpidevFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for SpidevFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        if writer.avail() > BUF_SIZE {
+            return_errno_with_message!(Errno::EMSGSIZE, "the SPI message is too long");
+        }
+
+        let mut buf = vec![0u8; writer.avail()];
+        self.device.read(&mut buf)?;
+
+        writer.write_fallible(&mut buf.as_slice().into())?;
+        Ok(buf.len())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        if reader.remain() > BUF_SIZE {
+            return_errno_with_message!(Errno::EMSGSIZE, "the SPI message is too long");
+        }
+
+        let mut buf = vec![0u8; reader.remain()];
+        reader.read_fallible(&mut buf.as_mut_slice().into())?;
+        self.device.write(&buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let config = self.device.config();
+
+        match cmd {
+            IoctlCmd::SPI_IOC_RD_MODE => {
+                current_userspace!().write_val(arg, &(config.mode.bits() as u8))?;
+            }
+            IoctlCmd::SPI_IOC_RD_MODE32 => {
+                current_userspace!().write_val(arg, &config.mode.bits())?;
+            }
+            IoctlCmd::SPI_IOC_RD_LSB_FIRST => {
+                let is_lsb_first = config.mode.contains(SpiMode::LSB_FIRST) as u8;
+                current_userspace!().write_val(arg, &is_lsb_first)?;
+            }
+            IoctlCmd::SPI_IOC_RD_BITS_PER_WORD => {
+                current_userspace!().write_val(arg, &config.bits_per_word)?;
+            }
+            IoctlCmd::SPI_IOC_RD_MAX_SPEED_HZ => {
+                current_userspace!().write_val(arg, &config.max_speed_hz)?;
+            }
+            IoctlCmd::SPI_IOC_WR_MODE => {
+                let mode = current_userspace!().read_val::<u8>(arg)?;
+                self.set_mode(mode as u32)?;
+            }
+            IoctlCmd::SPI_IOC_WR_MODE32 => {
+                let mode = current_userspace!().read_val::<u32>(arg)?;
+                self.set_mode(mode)?;
+            }
+            IoctlCmd::SPI_IOC_WR_LSB_FIRST => {
+                let is_lsb_first = current_userspace!().read_val::<u8>(arg)?;
+                self.set_lsb_first(is_lsb_first != 0)?;
+            }
+            IoctlCmd::SPI_IOC_WR_BITS_PER_WORD => {
+                let bits_per_word = current_userspace!().read_val::<u8>(arg)?;
+                self.set_bits_per_word(bits_per_word)?;
+            }
+            IoctlCmd::SPI_IOC_WR_MAX_SPEED_HZ => {
+                let max_speed_hz = current_userspace!().read_val::<u32>(arg)?;
+                self.set_max_speed_hz(max_speed_hz)?;
+            }
+            IoctlCmd::SPI_IOC_MESSAGE_1
+            | IoctlCmd::SPI_IOC_MESSAGE_2
+            | IoctlCmd::SPI_IOC_MESSAGE_3
+            | IoctlCmd::SPI_IOC_MESSAGE_4
+            | IoctlCmd::SPI_IOC_MESSAGE_5
+            | IoctlCmd::SPI_IOC_MESSAGE_6
+            | IoctlCmd::SPI_IOC_MESSAGE_7
+            | IoctlCmd::SPI_IOC_MESSAGE_8 => {
+                // The number of transfers is encoded in the size of the argument.
+                let nr_transfers = cmd.size() / size_of::<c_spi_ioc_transfer>();
+                return self.transfer_message(arg, nr_transfers);
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        Ok(0)
+    }
+}
+
+impl From<SpiError> for Error {
+    fn from(err: SpiError) -> Self {
+        match err {
+            SpiError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the SPI transfer is not supported")
+            }
+            SpiError::InvalidArgs => {
+                Error::with_message(Errno::EINVAL, "the SPI transfer is invalid")
+            }
+            SpiError::Timeout => {
+                Error::with_message(Errno::ETIMEDOUT, "the SPI transfer is timed out")
+            }
+            SpiError::Io => Error::with_message(Errno::EIO, "the SPI transfer fails"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/spi/spidev.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_spi_ioc_transfer {
+    tx_buf: u64,
+    rx_buf: u64,
+    len: u32,
+    speed_hz: u32,
+    delay_usecs: u16,
+    bits_per_word: u8,
+    cs_change: u8,
+    tx_nbits: u8,
+    rx_nbits: u8,
+    word_delay_usecs: u8,
+    _pad: u8,
+}
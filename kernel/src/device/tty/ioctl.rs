@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `ioctl` commands common to all terminals with a line discipline.
+//!
+//! The TTY, the PTY, and the serial terminals differ only in how they drive the hardware (if any).
+//! So the terminals implement [`TtyOps`] to describe the differences, and forward the `ioctl`
+//! commands to [`tty_ioctl`], which takes care of the termios, the window size, the buffers, and
+//! the job control.
+
+use super::{
+    line_discipline::LineDiscipline,
+    termio::{KernelTermios, KernelTermios2},
+};
+use crate::{current_userspace, fs::utils::IoctlCmd, prelude::*, process::Terminal};
+
+/// The operations of a terminal with a line discipline.
+pub trait TtyOps: Terminal {
+    /// Returns the line discipline.
+    fn ldisc(&self) -> &LineDiscipline;
+
+    /// Sets the termios.
+    ///
+    /// Terminals backed by hardware may apply the termios to the hardware as well.
+    fn set_termios(&self, termios: KernelTermios) {
+        self.ldisc().set_termios(termios);
+    }
+
+    /// Waits until all the output is transmitted.
+    fn drain_output(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Discards all the pending input.
+    fn flush_input(&self) {
+        self.ldisc().drain_input();
+    }
+
+    /// Discards all the pending output.
+    ///
+    /// The output is never buffered by the line discipline, but it may be buffered by the
+    /// terminal, e.g., by the master side of a PTY.
+    fn flush_output(&self) {}
+
+    /// Returns the number of bytes in the output buffer.
+    fn output_len(&self) -> usize {
+        0
+    }
+}
+
+/// The `TCFLSH` argument that discards the pending input.
+const TCIFLUSH: usize = 0;
+/// The `TCFLSH` argument that discards the pending output.
+const TCOFLUSH: usize = 1;
+/// The `TCFLSH` argument that discards both the pending input and output.
+const TCIOFLUSH: usize = 2;
+
+/// Handles an `ioctl` command of a terminal.
+///
+/// `via_master` indicates whether the command is issued via the master side of a PTY, in which
+/// case `tty` is the slave side.
+pub fn tty_ioctl<T: TtyOps + 'static>(
+    tty: Arc<T>,
+    cmd: IoctlCmd,
+    arg: usize,
+    via_master: bool,
+) -> Result<i32> {
+    let ldisc = tty.ldisc();
+
+    match cmd {
+        IoctlCmd::TCGETS => {
+            let termios = ldisc.termios();
+            trace!("get termios = {:?}", termios);
+            current_userspace!().write_val(arg, &termios)?;
+        }
+        IoctlCmd::TCGETS2 => {
+            let termios = KernelTermios2::from_termios(ldisc.termios());
+            current_userspace!().write_val(arg, &termios)?;
+        }
+        IoctlCmd::TCSETS
+        | IoctlCmd::TCSETSW
+        | IoctlCmd::TCSETSF
+        | IoctlCmd::TCSETS2
+        | IoctlCmd::TCSETSW2
+        | IoctlCmd::TCSETSF2 => {
+            let termios = match cmd {
+                IoctlCmd::TCSETS | IoctlCmd::TCSETSW | IoctlCmd::TCSETSF => {
+                    current_userspace!().read_val(arg)?
+                }
+                _ => current_userspace!()
+                    .read_val::<KernelTermios2>(arg)?
+                    .to_termios()?,
+            };
+            debug!("set termios = {:?}", termios);
+
+            if !matches!(cmd, IoctlCmd::TCSETS | IoctlCmd::TCSETS2) {
+                tty.drain_output()?;
+            }
+            tty.set_termios(termios);
+            if matches!(cmd, IoctlCmd::TCSETSF | IoctlCmd::TCSETSF2) {
+                tty.flush_input();
+            }
+        }
+        IoctlCmd::TCSBRK => {
+            // Sending a break is not supported, but the output is drained in either case.
+            tty.drain_output()?;
+        }
+        IoctlCmd::TCFLSH => match arg {
+            TCIFLUSH => tty.flush_input(),
+            TCOFLUSH => tty.flush_output(),
+            TCIOFLUSH => {
+                tty.flush_input();
+                tty.flush_output();
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the flush queue is invalid"),
+        },
+        IoctlCmd::TIOCGWINSZ => {
+            let winsize = ldisc.window_size();
+            current_userspace!().write_val(arg, &winsize)?;
+        }
+        IoctlCmd::TIOCSWINSZ => {
+            let winsize = current_userspace!().read_val(arg)?;
+            ldisc.set_window_size(winsize);
+        }
+        IoctlCmd::FIONREAD => {
+            let buffer_len = ldisc.buffer_len() as i32;
+            current_userspace!().write_val(arg, &buffer_len)?;
+        }
+        IoctlCmd::TIOCOUTQ => {
+            let output_len = tty.output_len() as i32;
+            current_userspace!().write_val(arg, &output_len)?;
+        }
+        _ => (tty as Arc<dyn Terminal>).job_ioctl(cmd, arg, via_master)?,
+    }
+
+    Ok(0)
+}
@@ -1,18 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::format;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use ostd::{
     sync::LocalIrqDisabled,
     trap::{disable_local, in_interrupt_context},
 };
 
-use super::termio::{KernelTermios, WinSize, CC_C_CHAR};
+use super::termio::{KernelTermios, WinSize, CC_C_CHAR, C_IFLAGS, C_LFLAGS, C_OFLAGS};
 use crate::{
     events::IoEvents,
     prelude::*,
     process::signal::{
-        constants::{SIGINT, SIGQUIT},
+        constants::{SIGINT, SIGQUIT, SIGTSTP, SIGWINCH},
         signals::kernel::KernelSignal,
         PollHandle, Pollable, Pollee,
     },
@@ -23,7 +26,8 @@ use crate::{
 // This implementation refers the implementation of linux
 // https://elixir.bootlin.com/linux/latest/source/include/linux/tty_ldisc.h
 
-const BUFFER_CAPACITY: usize = 4096;
+/// The capacity of the read buffer.
+pub const BUFFER_CAPACITY: usize = 4096;
 
 pub type LdiscSignalSender = Arc<dyn Fn(KernelSignal) + Send + Sync + 'static>;
 
@@ -43,6 +47,8 @@ pub struct LineDiscipline {
     winsize: SpinLock<WinSize, LocalIrqDisabled>,
     /// Pollee
     pollee: Pollee,
+    /// Whether the terminal is hung up
+    is_hung_up: AtomicBool,
     /// Used to send signal for foreground processes, when some char comes.
     send_signal: LdiscSignalSender,
     /// Work item
@@ -52,13 +58,13 @@ pub struct LineDiscipline {
 }
 
 pub struct CurrentLine {
-    buffer: RingBuffer<u8>,
+    buffer: Vec<u8>,
 }
 
 impl Default for CurrentLine {
     fn default() -> Self {
         Self {
-            buffer: RingBuffer::new(BUFFER_CAPACITY),
+            buffer: Vec::with_capacity(BUFFER_CAPACITY),
         }
     }
 }
@@ -66,23 +72,48 @@ impl Default for CurrentLine {
 impl CurrentLine {
     /// Reads all bytes inside current line and clear current line
     pub fn drain(&mut self) -> Vec<u8> {
-        let mut ret = vec![0u8; self.buffer.len()];
-        self.buffer.pop_slice(ret.as_mut_slice()).unwrap();
-        ret
+        core::mem::replace(&mut self.buffer, Vec::with_capacity(BUFFER_CAPACITY))
     }
 
+    /// Pushes a char to the end of the line.
+    ///
+    /// Like Linux, the char is discarded if the line is full.
     pub fn push_char(&mut self, char: u8) {
-        // What should we do if line is full?
-        debug_assert!(!self.is_full());
-        self.buffer.push_overwrite(char);
+        if !self.is_full() {
+            self.buffer.push(char);
+        }
     }
 
-    pub fn backspace(&mut self) {
-        let _ = self.buffer.pop();
+    /// Erases the last char of the line, returning whether a char is erased.
+    pub fn backspace(&mut self) -> bool {
+        self.buffer.pop().is_some()
+    }
+
+    /// Erases the last word of the line, returning the number of erased chars.
+    ///
+    /// The whitespace after the word is erased as well.
+    pub fn erase_word(&mut self) -> usize {
+        let old_len = self.buffer.len();
+        while self
+            .buffer
+            .last()
+            .is_some_and(|ch| ch.is_ascii_whitespace())
+        {
+            self.buffer.pop();
+        }
+        while self
+            .buffer
+            .last()
+            .is_some_and(|ch| !ch.is_ascii_whitespace())
+        {
+            self.buffer.pop();
+        }
+        old_len - self.buffer.len()
     }
 
     pub fn is_full(&self) -> bool {
-        self.buffer.is_full()
+        // Leave room for the line terminator.
+        self.buffer.len() >= BUFFER_CAPACITY - 1
     }
 
     pub fn is_empty(&self) -> bool {
@@ -113,6 +144,7 @@ impl LineDiscipline {
                 termios: SpinLock::new(KernelTermios::default()),
                 winsize: SpinLock::new(WinSize::default()),
                 pollee: Pollee::new(),
+                is_hung_up: AtomicBool::new(false),
                 send_signal,
                 work_item,
                 work_item_para: Arc::new(SpinLock::new(LineDisciplineWorkPara::new())),
@@ -121,28 +153,28 @@ impl LineDiscipline {
     }
 
     /// Pushes a char to the line discipline
-    pub fn push_char<F2: FnMut(&str)>(&self, ch: u8, echo_callback: F2) {
+    pub fn push_char<F: FnMut(&[u8])>(&self, ch: u8, mut echo_callback: F) {
         let termios = self.termios.lock();
 
-        let ch = if termios.contains_icrnl() && ch == b'\r' {
-            b'\n'
-        } else {
-            ch
+        let Some(ch) = map_input_char(ch, &termios) else {
+            return;
         };
 
         if self.may_send_signal(&termios, ch) {
             submit_work_item(self.work_item.clone(), WorkPriority::High);
-            // CBREAK mode may require the character to be outputted, so just go ahead.
-        }
-
-        // Typically, a tty in raw mode does not echo. But the tty can also be in a CBREAK mode,
-        // with ICANON closed and ECHO opened.
-        if termios.contain_echo() {
-            self.output_char(ch, &termios, echo_callback);
+            if termios.contain_echo() {
+                echo_callback(&echo_char(ch, &termios));
+            }
+            return;
         }
 
         // Raw mode
         if !termios.is_canonical_mode() {
+            // Typically, a tty in raw mode does not echo. But the tty can also be in a CBREAK
+            // mode, with ICANON closed and ECHO opened.
+            if termios.contain_echo() {
+                echo_callback(&echo_char(ch, &termios));
+            }
             self.read_buffer.lock().push_overwrite(ch);
             self.pollee.notify(IoEvents::IN);
             return;
@@ -150,47 +182,90 @@ impl LineDiscipline {
 
         // Canonical mode
 
-        if ch == *termios.get_special_char(CC_C_CHAR::VKILL) {
+        let lflags = termios.lflags();
+        let is_echo = termios.contain_echo();
+
+        if termios.is_special_char(CC_C_CHAR::VERASE, ch) {
+            // Type backspace
+            let is_erased = self.current_line.lock().backspace();
+            if is_echo && is_erased {
+                if lflags.contains(C_LFLAGS::ECHOE) {
+                    echo_callback(ERASE_SEQUENCE);
+                } else {
+                    echo_callback(&echo_char(ch, &termios));
+                }
+            }
+            return;
+        }
+
+        if termios.is_special_char(CC_C_CHAR::VKILL, ch) {
             // Erase current line
-            self.current_line.lock().drain();
+            let erased_len = self.current_line.lock().drain().len();
+            if is_echo {
+                if lflags.contains(C_LFLAGS::ECHOKE) {
+                    echo_callback(&ERASE_SEQUENCE.repeat(erased_len));
+                } else {
+                    echo_callback(&echo_char(ch, &termios));
+                    if lflags.contains(C_LFLAGS::ECHOK) {
+                        echo_callback(&output_chars(b"\n", &termios));
+                    }
+                }
+            }
+            return;
         }
 
-        if ch == *termios.get_special_char(CC_C_CHAR::VERASE) {
-            // Type backspace
-            let mut current_line = self.current_line.lock();
-            if !current_line.is_empty() {
-                current_line.backspace();
+        if termios.contains_iexten() && termios.is_special_char(CC_C_CHAR::VWERASE, ch) {
+            // Erase the last word
+            let erased_len = self.current_line.lock().erase_word();
+            if is_echo && lflags.contains(C_LFLAGS::ECHOE) {
+                echo_callback(&ERASE_SEQUENCE.repeat(erased_len));
             }
+            return;
         }
 
         if is_line_terminator(ch, &termios) {
+            if is_echo && !is_eof(ch, &termios) {
+                echo_callback(&echo_char(ch, &termios));
+            } else if ch == b'\n' && lflags.contains(C_LFLAGS::ECHONL) {
+                echo_callback(&output_chars(b"\n", &termios));
+            }
+
             // If a new line is met, all bytes in current_line will be moved to read_buffer
-            let mut current_line = self.current_line.lock();
-            current_line.push_char(ch);
-            let current_line_chars = current_line.drain();
+            let current_line_chars = self.current_line.lock().drain();
+            let mut read_buffer = self.read_buffer.lock();
             for char in current_line_chars {
-                self.read_buffer.lock().push_overwrite(char);
-                self.pollee.notify(IoEvents::IN);
+                read_buffer.push_overwrite(char);
             }
+            read_buffer.push_overwrite(ch);
+            self.pollee.notify(IoEvents::IN);
+            return;
         }
 
-        if is_printable_char(ch) {
-            // Printable character
-            self.current_line.lock().push_char(ch);
+        if is_echo {
+            echo_callback(&echo_char(ch, &termios));
         }
+        self.current_line.lock().push_char(ch);
     }
 
     fn may_send_signal(&self, termios: &KernelTermios, ch: u8) -> bool {
-        if !termios.is_canonical_mode() || !termios.contains_isig() {
+        if !termios.contains_isig() {
             return false;
         }
 
-        let signal = match ch {
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VINTR) => KernelSignal::new(SIGINT),
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VQUIT) => KernelSignal::new(SIGQUIT),
-            _ => return false,
+        let signal = if termios.is_special_char(CC_C_CHAR::VINTR, ch) {
+            KernelSignal::new(SIGINT)
+        } else if termios.is_special_char(CC_C_CHAR::VQUIT, ch) {
+            KernelSignal::new(SIGQUIT)
+        } else if termios.is_special_char(CC_C_CHAR::VSUSP, ch) {
+            KernelSignal::new(SIGTSTP)
+        } else {
+            return false;
         };
 
+        if !termios.lflags().contains(C_LFLAGS::NOFLSH) {
+            self.drain_input();
+        }
+
         if in_interrupt_context() {
             // `kernel_signal()` may cause sleep, so only construct parameters here.
             self.work_item_para.lock().kernel_signal = Some(signal);
@@ -202,6 +277,10 @@ impl LineDiscipline {
     }
 
     fn check_io_events(&self) -> IoEvents {
+        if self.is_hung_up() {
+            return IoEvents::IN | IoEvents::OUT | IoEvents::HUP;
+        }
+
         let buffer = self.read_buffer.lock();
 
         if !buffer.is_empty() {
@@ -218,54 +297,79 @@ impl LineDiscipline {
         };
     }
 
-    // TODO: respect output flags
-    fn output_char<F: FnMut(&str)>(&self, ch: u8, termios: &KernelTermios, mut echo_callback: F) {
-        match ch {
-            b'\n' => echo_callback("\n"),
-            b'\r' => echo_callback("\r\n"),
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VERASE) => {
-                // write a space to overwrite current character
-                let backspace: &str = core::str::from_utf8(b"\x08 \x08").unwrap();
-                echo_callback(backspace);
-            }
-            ch if is_printable_char(ch) => print!("{}", char::from(ch)),
-            ch if is_ctrl_char(ch) && termios.contains_echo_ctl() => {
-                let ctrl_char = format!("^{}", get_printable_char(ch));
-                echo_callback(&ctrl_char);
-            }
-            _ => {}
-        }
-    }
-
-    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        self.wait_events(IoEvents::IN, None, || self.try_read(buf))
+    /// Processes the bytes written to the terminal according to the output flags.
+    pub fn process_output(&self, buf: &[u8]) -> Vec<u8> {
+        output_chars(buf, &self.termios.lock())
     }
 
-    /// Reads all bytes buffered to `dst`.
+    /// Reads bytes to `buf`.
     ///
-    /// This method returns the actual read length.
-    fn try_read(&self, dst: &mut [u8]) -> Result<usize> {
+    /// In the canonical mode, this method blocks until a line is available. Otherwise, the
+    /// behavior is controlled by `VMIN` and `VTIME` as described in termios(3):
+    /// - `VMIN == 0, VTIME == 0`: Returns the available bytes immediately;
+    /// - `VMIN > 0, VTIME == 0`: Blocks until `VMIN` bytes are available;
+    /// - `VMIN == 0, VTIME > 0`: Blocks until a byte is available or `VTIME` expires;
+    /// - `VMIN > 0, VTIME > 0`: Blocks until a byte is available, then waits until `VMIN` bytes
+    ///   are available or `VTIME` expires.
+    ///
+    /// `VTIME` is in tenths of a second. Unlike Linux, the timer in the last case is not
+    /// restarted after each byte is received.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
         let (vmin, vtime) = {
             let termios = self.termios.lock();
-            let vmin = *termios.get_special_char(CC_C_CHAR::VMIN);
-            let vtime = *termios.get_special_char(CC_C_CHAR::VTIME);
-            (vmin, vtime)
-        };
-        let read_len = {
-            if vmin == 0 && vtime == 0 {
-                // poll read
-                self.poll_read(dst)
-            } else if vmin > 0 && vtime == 0 {
-                // block read
-                self.block_read(dst, vmin)?
-            } else if vmin == 0 && vtime > 0 {
-                todo!()
-            } else if vmin > 0 && vtime > 0 {
-                todo!()
+            if termios.is_canonical_mode() {
+                (1, 0)
             } else {
-                unreachable!()
+                let vmin = *termios.get_special_char(CC_C_CHAR::VMIN);
+                let vtime = *termios.get_special_char(CC_C_CHAR::VTIME);
+                (vmin, vtime)
             }
         };
+        let timeout = Duration::from_millis(vtime as u64 * 100);
+
+        match (vmin, vtime) {
+            (0, 0) => self.try_read(buf, 0),
+            (vmin, 0) => self.wait_events(IoEvents::IN, None, || self.try_read(buf, vmin)),
+            (0, _) => {
+                let result =
+                    self.wait_events(IoEvents::IN, Some(&timeout), || self.try_read(buf, 1));
+                match result {
+                    Err(err) if err.error() == Errno::ETIME => Ok(0),
+                    result => result,
+                }
+            }
+            (vmin, _) => {
+                self.wait_events(IoEvents::IN, None, || {
+                    if self.is_empty() && !self.is_hung_up() {
+                        return_errno_with_message!(Errno::EAGAIN, "the buffer is empty");
+                    }
+                    Ok(())
+                })?;
+                let result =
+                    self.wait_events(IoEvents::IN, Some(&timeout), || self.try_read(buf, vmin));
+                match result {
+                    Err(err) if err.error() == Errno::ETIME => self.try_read(buf, 0),
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Reads all bytes buffered to `dst`.
+    ///
+    /// This method returns the actual read length. If the terminal is hung up, this method
+    /// returns 0 (i.e., the end of file).
+    ///
+    /// # Errors
+    ///
+    /// If the available bytes are fewer than `min(dst.len(), vmin)`,
+    /// this method returns [`Errno::EAGAIN`].
+    fn try_read(&self, dst: &mut [u8], vmin: u8) -> Result<usize> {
+        if self.is_hung_up() {
+            return Ok(0);
+        }
+
+        let read_len = self.block_read(dst, vmin)?;
         self.pollee.invalidate();
         Ok(read_len)
     }
@@ -349,6 +453,20 @@ impl LineDiscipline {
         self.pollee.invalidate();
     }
 
+    /// Hangs up the terminal.
+    ///
+    /// After the terminal is hung up, reads return the end of file immediately.
+    pub fn hang_up(&self) {
+        self.is_hung_up.store(true, Ordering::Relaxed);
+        self.pollee
+            .notify(IoEvents::IN | IoEvents::OUT | IoEvents::HUP);
+    }
+
+    /// Returns whether the terminal is hung up.
+    pub fn is_hung_up(&self) -> bool {
+        self.is_hung_up.load(Ordering::Relaxed)
+    }
+
     pub fn buffer_len(&self) -> usize {
         self.read_buffer.lock().len()
     }
@@ -357,20 +475,86 @@ impl LineDiscipline {
         *self.winsize.lock()
     }
 
+    /// Sets the window size.
+    ///
+    /// If the window size changes, `SIGWINCH` is sent to the foreground process group.
     pub fn set_window_size(&self, winsize: WinSize) {
-        *self.winsize.lock() = winsize;
+        {
+            let mut old_winsize = self.winsize.lock();
+            if *old_winsize == winsize {
+                return;
+            }
+            *old_winsize = winsize;
+        }
+
+        (self.send_signal)(KernelSignal::new(SIGWINCH));
     }
 }
 
+/// The sequence that erases a character on the screen.
+const ERASE_SEQUENCE: &[u8] = b"\x08 \x08";
+
+/// Maps an input char according to the input flags.
+///
+/// Returns `None` if the char should be ignored.
+fn map_input_char(ch: u8, termios: &KernelTermios) -> Option<u8> {
+    let iflags = termios.iflags();
+
+    let mut ch = ch;
+    if iflags.contains(C_IFLAGS::ISTRIP) {
+        ch &= 0x7f;
+    }
+    if iflags.contains(C_IFLAGS::IUCLC) && termios.contains_iexten() {
+        ch = ch.to_ascii_lowercase();
+    }
+
+    match ch {
+        b'\r' if iflags.contains(C_IFLAGS::IGNCR) => None,
+        b'\r' if iflags.contains(C_IFLAGS::ICRNL) => Some(b'\n'),
+        b'\n' if iflags.contains(C_IFLAGS::INLCR) => Some(b'\r'),
+        ch => Some(ch),
+    }
+}
+
+/// Maps output chars according to the output flags.
+// TODO: Support `ONOCR` and `ONLRET`, which require tracking the column of the cursor.
+fn output_chars(chars: &[u8], termios: &KernelTermios) -> Vec<u8> {
+    let oflags = termios.oflags();
+    if !oflags.contains(C_OFLAGS::OPOST) {
+        return chars.to_vec();
+    }
+
+    let mut output = Vec::with_capacity(chars.len());
+    for ch in chars.iter().copied() {
+        match ch {
+            b'\n' if oflags.contains(C_OFLAGS::ONLCR) => output.extend_from_slice(b"\r\n"),
+            b'\r' if oflags.contains(C_OFLAGS::OCRNL) => output.push(b'\n'),
+            ch if oflags.contains(C_OFLAGS::OLCUC) => output.push(ch.to_ascii_uppercase()),
+            ch => output.push(ch),
+        }
+    }
+    output
+}
+
+/// Returns the chars to echo for an input char.
+fn echo_char(ch: u8, termios: &KernelTermios) -> Vec<u8> {
+    if is_ctrl_char(ch) && termios.contains_echo_ctl() {
+        // Control chars are echoed as `^X`, and DEL is echoed as `^?`.
+        return vec![b'^', ch ^ 0x40];
+    }
+
+    output_chars(&[ch], termios)
+}
+
 fn is_line_terminator(item: u8, termios: &KernelTermios) -> bool {
     if item == b'\n'
-        || item == *termios.get_special_char(CC_C_CHAR::VEOF)
-        || item == *termios.get_special_char(CC_C_CHAR::VEOL)
+        || termios.is_special_char(CC_C_CHAR::VEOF, item)
+        || termios.is_special_char(CC_C_CHAR::VEOL, item)
     {
         return true;
     }
 
-    if termios.contains_iexten() && item == *termios.get_special_char(CC_C_CHAR::VEOL2) {
+    if termios.contains_iexten() && termios.is_special_char(CC_C_CHAR::VEOL2, item) {
         return true;
     }
 
@@ -378,24 +562,15 @@ fn is_line_terminator(item: u8, termios: &KernelTermios) -> bool {
 }
 
 fn is_eof(ch: u8, termios: &KernelTermios) -> bool {
-    ch == *termios.get_special_char(CC_C_CHAR::VEOF)
-}
-
-fn is_printable_char(ch: u8) -> bool {
-    (0x20..0x7f).contains(&ch)
+    termios.is_special_char(CC_C_CHAR::VEOF, ch)
 }
 
 fn is_ctrl_char(ch: u8) -> bool {
-    if ch == b'\r' || ch == b'\n' {
+    if ch == b'\r' || ch == b'\n' || ch == b'\t' {
         return false;
     }
 
-    (0..0x20).contains(&ch)
-}
-
-fn get_printable_char(ctrl_char: u8) -> char {
-    debug_assert!(is_ctrl_char(ctrl_char));
-    char::from_u32((ctrl_char + b'A' - 1) as u32).unwrap()
+    ch < 0x20 || ch == 0x7f
 }
 
 struct LineDisciplineWorkPara {
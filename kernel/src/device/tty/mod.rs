@@ -3,9 +3,8 @@
 use ostd::early_print;
 use spin::Once;
 
-use self::{driver::TtyDriver, line_discipline::LineDiscipline};
+use self::{driver::TtyDriver, ioctl::TtyOps, line_discipline::LineDiscipline};
 use crate::{
-    current_userspace,
     events::IoEvents,
     fs::{
         device::{Device, DeviceId, DeviceType},
@@ -21,6 +20,7 @@ use crate::{
 
 mod device;
 pub mod driver;
+pub mod ioctl;
 pub mod line_discipline;
 pub mod termio;
 
@@ -66,8 +66,12 @@ impl Tty {
     pub fn push_char(&self, ch: u8) {
         // FIXME: Use `early_print` to avoid calling virtio-console.
         // This is only a workaround
-        self.ldisc
-            .push_char(ch, |content| early_print!("{}", content))
+        // FIXME: Partial UTF-8 sequences are not echoed.
+        self.ldisc.push_char(ch, |content| {
+            if let Ok(content) = core::str::from_utf8(content) {
+                early_print!("{}", content);
+            }
+        })
     }
 }
 
@@ -97,45 +101,7 @@ impl FileIo for Tty {
     }
 
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
-        match cmd {
-            IoctlCmd::TCGETS => {
-                // Get terminal attributes
-                let termios = self.ldisc.termios();
-                trace!("get termios = {:?}", termios);
-                current_userspace!().write_val(arg, &termios)?;
-            }
-            IoctlCmd::TCSETS => {
-                // Set terminal attributes
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-            }
-            IoctlCmd::TCSETSW => {
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-                // TODO: drain output buffer
-            }
-            IoctlCmd::TCSETSF => {
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-                self.ldisc.drain_input();
-                // TODO: drain output buffer
-            }
-            IoctlCmd::TIOCGWINSZ => {
-                let winsize = self.ldisc.window_size();
-                current_userspace!().write_val(arg, &winsize)?;
-            }
-            IoctlCmd::TIOCSWINSZ => {
-                let winsize = current_userspace!().read_val(arg)?;
-                self.ldisc.set_window_size(winsize);
-            }
-            _ => (self.weak_self.upgrade().unwrap() as Arc<dyn Terminal>)
-                .job_ioctl(cmd, arg, false)?,
-        }
-
-        Ok(0)
+        ioctl::tty_ioctl(self.weak_self.upgrade().unwrap(), cmd, arg, false)
     }
 }
 
@@ -145,6 +111,12 @@ impl Terminal for Tty {
     }
 }
 
+impl TtyOps for Tty {
+    fn ldisc(&self) -> &LineDiscipline {
+        &self.ldisc
+    }
+}
+
 impl Device for Tty {
     fn type_(&self) -> DeviceType {
         DeviceType::CharDevice
@@ -87,12 +87,46 @@ impl C_CFLAGS {
         Ok(C_CFLAGS_CSIZE::try_from(csize)?)
     }
 
+    pub fn set_cbaud(&mut self, cbaud: C_CFLAGS_BAUD) {
+        self.0 = (self.0 & !CBAUD_MASK) | cbaud as u32;
+    }
+
     pub fn cread(&self) -> bool {
         self.0 & CREAD != 0
     }
+
+    /// Returns whether two stop bits are used.
+    pub fn cstopb(&self) -> bool {
+        self.0 & CSTOPB != 0
+    }
+
+    /// Returns whether the parity is enabled.
+    pub fn parenb(&self) -> bool {
+        self.0 & PARENB != 0
+    }
+
+    /// Returns whether the parity is odd (or mark, if [`Self::cmspar`] is set).
+    pub fn parodd(&self) -> bool {
+        self.0 & PARODD != 0
+    }
+
+    /// Returns whether the parity is stick, i.e., mark or space.
+    pub fn cmspar(&self) -> bool {
+        self.0 & CMSPAR != 0
+    }
+
+    /// Returns whether the hardware flow control with RTS/CTS is enabled.
+    pub fn crtscts(&self) -> bool {
+        self.0 & CRTSCTS != 0
+    }
 }
 
+const CSTOPB: u32 = 0x00000040;
 const CREAD: u32 = 0x00000080;
+const PARENB: u32 = 0x00000100;
+const PARODD: u32 = 0x00000200;
+const CMSPAR: u32 = 0x40000000;
+const CRTSCTS: u32 = 0x80000000;
 const CBAUD_MASK: u32 = 0x0000100f;
 const CSIZE_MASK: u32 = 0x00000030;
 
@@ -124,6 +158,75 @@ pub enum C_CFLAGS_BAUD {
     B9600 = 0x0000000d,
     B19200 = 0x0000000e,
     B38400 = 0x0000000f,
+    // The extended baud rates (CBAUDEX).
+    B57600 = 0x00001001,
+    B115200 = 0x00001002,
+    B230400 = 0x00001003,
+    B460800 = 0x00001004,
+    B500000 = 0x00001005,
+    B576000 = 0x00001006,
+    B921600 = 0x00001007,
+    B1000000 = 0x00001008,
+    B1152000 = 0x00001009,
+    B1500000 = 0x0000100a,
+    B2000000 = 0x0000100b,
+    B2500000 = 0x0000100c,
+    B3000000 = 0x0000100d,
+    B3500000 = 0x0000100e,
+    B4000000 = 0x0000100f,
+}
+
+impl C_CFLAGS_BAUD {
+    const SPEEDS: [(C_CFLAGS_BAUD, u32); 31] = [
+        (Self::B0, 0),
+        (Self::B50, 50),
+        (Self::B75, 75),
+        (Self::B110, 110),
+        (Self::B134, 134),
+        (Self::B150, 150),
+        (Self::B200, 200),
+        (Self::B300, 300),
+        (Self::B600, 600),
+        (Self::B1200, 1200),
+        (Self::B1800, 1800),
+        (Self::B2400, 2400),
+        (Self::B4800, 4800),
+        (Self::B9600, 9600),
+        (Self::B19200, 19200),
+        (Self::B38400, 38400),
+        (Self::B57600, 57600),
+        (Self::B115200, 115200),
+        (Self::B230400, 230400),
+        (Self::B460800, 460800),
+        (Self::B500000, 500000),
+        (Self::B576000, 576000),
+        (Self::B921600, 921600),
+        (Self::B1000000, 1000000),
+        (Self::B1152000, 1152000),
+        (Self::B1500000, 1500000),
+        (Self::B2000000, 2000000),
+        (Self::B2500000, 2500000),
+        (Self::B3000000, 3000000),
+        (Self::B3500000, 3500000),
+        (Self::B4000000, 4000000),
+    ];
+
+    /// Returns the baud rate in bits per second.
+    pub fn speed(&self) -> u32 {
+        Self::SPEEDS
+            .iter()
+            .find(|(baud, _)| *baud as u32 == *self as u32)
+            .unwrap()
+            .1
+    }
+
+    /// Returns the baud whose baud rate is exactly `speed`.
+    pub fn from_speed(speed: u32) -> Option<Self> {
+        Self::SPEEDS
+            .iter()
+            .find(|(_, baud_speed)| *baud_speed == speed)
+            .map(|(baud, _)| *baud)
+    }
 }
 
 bitflags! {
@@ -261,6 +364,15 @@ impl KernelTermios {
         &mut self.c_cc[cc_c_char as usize]
     }
 
+    /// Returns whether `ch` is the special character.
+    ///
+    /// A special character can be disabled by setting it to `_POSIX_VDISABLE`, in which case no
+    /// character matches it.
+    pub fn is_special_char(&self, cc_c_char: CC_C_CHAR, ch: u8) -> bool {
+        let special_char = *self.get_special_char(cc_c_char);
+        special_char != POSIX_VDISABLE && ch == special_char
+    }
+
     /// Canonical mode means we will handle input by lines, not by single character
     pub fn is_canonical_mode(&self) -> bool {
         self.c_lflags.contains(C_LFLAGS::ICANON)
@@ -286,6 +398,77 @@ impl KernelTermios {
     pub fn contains_iexten(&self) -> bool {
         self.c_lflags.contains(C_LFLAGS::IEXTEN)
     }
+
+    /// ONLCR with OPOST means we should map \n to \r\n on output
+    pub fn contains_onlcr(&self) -> bool {
+        self.c_oflags.contains(C_OFLAGS::OPOST | C_OFLAGS::ONLCR)
+    }
+
+    pub fn iflags(&self) -> C_IFLAGS {
+        self.c_iflags
+    }
+
+    pub fn oflags(&self) -> C_OFLAGS {
+        self.c_oflags
+    }
+
+    pub fn lflags(&self) -> C_LFLAGS {
+        self.c_lflags
+    }
+
+    pub fn cflags(&self) -> C_CFLAGS {
+        self.c_cflags
+    }
+
+    pub fn cflags_mut(&mut self) -> &mut C_CFLAGS {
+        &mut self.c_cflags
+    }
+}
+
+/// The value that disables a special character.
+const POSIX_VDISABLE: CcT = 0;
+
+/// The termios with arbitrary baud rates (i.e., `struct termios2` in Linux).
+///
+/// If the baud of `c_cflags` is `BOTHER`, the baud rates are specified by `c_ispeed` and
+/// `c_ospeed` instead.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct KernelTermios2 {
+    termios: KernelTermios,
+    c_ispeed: SpeedT,
+    c_ospeed: SpeedT,
+}
+
+/// The baud that indicates the baud rates are specified in `struct termios2`.
+const BOTHER: u32 = 0x00001000;
+
+impl KernelTermios2 {
+    pub fn from_termios(termios: KernelTermios) -> Self {
+        let speed = termios.c_cflags.cbaud().map_or(0, |baud| baud.speed());
+        Self {
+            termios,
+            c_ispeed: speed,
+            c_ospeed: speed,
+        }
+    }
+
+    /// Converts to the termios.
+    ///
+    /// # Errors
+    ///
+    /// The baud rates specified with `BOTHER` must be one of the standard baud rates, otherwise
+    /// this method returns [`Errno::EINVAL`].
+    pub fn to_termios(&self) -> Result<KernelTermios> {
+        let mut termios = self.termios;
+        if termios.c_cflags.0 & CBAUD_MASK == BOTHER {
+            let Some(baud) = C_CFLAGS_BAUD::from_speed(self.c_ospeed) else {
+                return_errno_with_message!(Errno::EINVAL, "the baud rate is not supported");
+            };
+            termios.c_cflags.set_cbaud(baud);
+        }
+        Ok(termios)
+    }
 }
 
 const fn control_character(c: char) -> u8 {
@@ -293,7 +476,7 @@ const fn control_character(c: char) -> u8 {
     c as u8 - b'A' + 1u8
 }
 
-#[derive(Debug, Clone, Copy, Default, Pod)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Pod)]
 #[repr(C)]
 pub struct WinSize {
     ws_row: u16,
@@ -301,3 +484,15 @@ pub struct WinSize {
     ws_xpixel: u16,
     ws_ypixel: u16,
 }
+
+impl WinSize {
+    /// Creates a window size in characters, with the size in pixels unknown.
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::*;
+use crate::{
+    events::IoEvents,
+    fs::inode_handle::FileIo,
+    net::iface::TunFile,
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The minor device number of `/dev/net/tun`.
+pub(super) const TUN_MINOR: u32 = 200;
+
+/// The TUN/TAP device.
+///
+/// Each opening of `/dev/net/tun` creates a new file, which is attached to a TUN/TAP device by
+/// `TUNSETIFF`.
+pub struct Tun;
+
+impl Device for Tun {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(10, TUN_MINOR)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(TunFile::new())))
+    }
+}
+
+impl Pollable for Tun {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for Tun {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the TUN/TAP device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the TUN/TAP device is not opened");
+    }
+}
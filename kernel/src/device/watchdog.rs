@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The watchdog devices (`/dev/watchdogN`), which let a supervisor in the user space reset the
+//! system if it hangs.
+//!
+//! The first watchdog is also exposed as `/dev/watchdog`, which is the legacy misc device.
+//!
+//! Reference: <https://docs.kernel.org/watchdog/watchdog-api.html>
+
+use aster_watchdog::{
+    WatchdogDevice, WatchdogError, WatchdogHandle, IDENTITY_MAX, MAGIC_CLOSE_CHAR,
+};
+
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major device number of the watchdog devices.
+///
+/// Linux allocates the number dynamically, so a fixed number in the dynamic range is used.
+pub(super) const WATCHDOG_MAJOR: u32 = 247;
+
+/// The minor device number of `/dev/watchdog`, whose major device number is that of the misc
+/// devices.
+pub(super) const WATCHDOG_MISC_MINOR: u32 = 130;
+
+// The options of `WDIOC_SETOPTIONS`.
+const WDIOS_DISABLECARD: i32 = 0x0001;
+const WDIOS_ENABLECARD: i32 = 0x0002;
+
+pub(super) fn init() -> Result<()> {
+    for device in aster_watchdog::all_devices() {
+        let name = device.name();
+        if device.index() == 0 {
+            add_node(
+                Arc::new(WatchdogDev {
+                    device: device.clone(),
+                    is_misc: true,
+                }),
+                "watchdog",
+                "misc",
+            )?;
+        }
+        add_node(
+            Arc::new(WatchdogDev {
+                device,
+                is_misc: false,
+            }),
+            &name,
+            "watchdog",
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the device of the minor device number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(device) = aster_watchdog::get_device(minor) else {
+        return_errno_with_message!(Errno::ENODEV, "the watchdog does not exist");
+    };
+    Ok(Arc::new(WatchdogDev {
+        device,
+        is_misc: false,
+    }))
+}
+
+/// Returns the device of `/dev/watchdog`.
+pub(super) fn get_misc_device() -> Result<Arc<dyn Device>> {
+    let Some(device) = aster_watchdog::get_device(0) else {
+        return_errno_with_message!(Errno::ENODEV, "the watchdog does not exist");
+    };
+    Ok(Arc::new(WatchdogDev {
+        device,
+        is_misc: true,
+    }))
+}
+
+struct WatchdogDev {
+    device: Arc<WatchdogDevice>,
+    is_misc: bool,
+}
+
+impl Device for WatchdogDev {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        if self.is_misc {
+            DeviceId::new(10, WATCHDOG_MISC_MINOR)
+        } else {
+            DeviceId::new(WATCHDOG_MAJOR, self.device.index())
+        }
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let handle = self.device.open()?;
+        Ok(Some(Arc::new(WatchdogFile { handle })))
+    }
+}
+
+impl Pollable for WatchdogDev {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for WatchdogDev {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog is not opened");
+    }
+}
+
+/// An opened watchdog device.
+struct WatchdogFile {
+    handle: WatchdogHandle,
+}
+
+impl WatchdogFile {
+    fn get_support(&self, arg: usize) -> Result<()> {
+        let mut info = c_watchdog_info {
+            options: self.handle.options().bits(),
+            firmware_version: 0,
+            identity: [0; IDENTITY_MAX],
+        };
+        let identity = self.handle.device().watchdog().identity().as_bytes();
+        let len = identity.len().min(IDENTITY_MAX - 1);
+        info.identity[..len].copy_from_slice(&identity[..len]);
+
+        current_userspace!().write_val(arg, &info)
+    }
+
+    fn set_options(&self, arg: usize) -> Result<()> {
+        let options: i32 = current_userspace!().read_val(arg)?;
+        if options & (WDIOS_DISABLECARD | WDIOS_ENABLECARD) == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the watchdog options are invalid");
+        }
+
+        if options & WDIOS_DISABLECARD != 0 {
+            self.handle.disable()?;
+        }
+        if options & WDIOS_ENABLECARD != 0 {
+            self.handle.enable()?;
+        }
+        Ok(())
+    }
+
+    fn set_timeout(&self, arg: usize) -> Result<()> {
+        let timeout: i32 = current_userspace!().read_val(arg)?;
+        let Ok(timeout) = u32::try_from(timeout) else {
+            return_errno_with_message!(Errno::EINVAL, "the timeout is negative");
+        };
+        self.handle.set_timeout(timeout)?;
+
+        // Like Linux, the timeout that takes effect is returned.
+        current_userspace!().write_val(arg, &(self.handle.timeout() as i32))
+    }
+}
+
+impl Pollable for WatchdogFile {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for WatchdogFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog cannot be read");
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        if len == 0 {
+            return Ok(0);
+        }
+
+        // Only whether the magic character is written matters, so the bytes are scanned in
+        // chunks instead of being copied at once.
+        let mut has_magic_close_char = false;
+        let mut buf = [0u8; 64];
+        while reader.has_remain() {
+            let chunk_len = reader.remain().min(buf.len());
+            reader.read_fallible(&mut (&mut buf[..chunk_len]).into())?;
+            has_magic_close_char |= buf[..chunk_len].contains(&MAGIC_CLOSE_CHAR);
+        }
+
+        self.handle.write(has_magic_close_char)?;
+        Ok(len)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+        match cmd {
+            IoctlCmd::WDIOC_GETSUPPORT => self.get_support(arg)?,
+            IoctlCmd::WDIOC_GETSTATUS => {
+                user_space.write_val(arg, &(self.handle.status().bits() as i32))?
+            }
+            IoctlCmd::WDIOC_GETBOOTSTATUS => {
+                user_space.write_val(arg, &(self.handle.boot_status().bits() as i32))?
+            }
+            IoctlCmd::WDIOC_SETOPTIONS => self.set_options(arg)?,
+            IoctlCmd::WDIOC_KEEPALIVE => self.handle.keepalive()?,
+            IoctlCmd::WDIOC_SETTIMEOUT => self.set_timeout(arg)?,
+            IoctlCmd::WDIOC_GETTIMEOUT => {
+                user_space.write_val(arg, &(self.handle.timeout() as i32))?
+            }
+            IoctlCmd::WDIOC_GETTIMELEFT => {
+                user_space.write_val(arg, &(self.handle.time_left()? as i32))?
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+
+        Ok(0)
+    }
+}
+
+impl From<WatchdogError> for Error {
+    fn from(err: WatchdogError) -> Self {
+        match err {
+            WatchdogError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the watchdog operation is not supported")
+            }
+            WatchdogError::InvalidArgs => {
+                Error::with_message(Errno::EINVAL, "the watchdog operation is invalid")
+            }
+            WatchdogError::Busy => Error::with_message(Errno::EBUSY, "the watchdog is opened"),
+            WatchdogError::Io => Error::with_message(Errno::EIO, "the watchdog operation fails"),
+        }
+    }
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/watchdog.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_watchdog_info {
+    options: u32,
+    firmware_version: u32,
+    identity: [u8; IDENTITY_MAX],
+}
@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::*;
+use crate::{
+    events::IoEvents,
+    fs::inode_handle::FileIo,
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The whiteout device.
+///
+/// A character device with the device number 0/0 is a whiteout, which is used by the overlay
+/// file system to mark the files that are deleted from the lower layers. It cannot be opened.
+pub struct Whiteout;
+
+impl Device for Whiteout {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(0, 0)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        return_errno_with_message!(Errno::ENXIO, "the whiteout device cannot be opened");
+    }
+}
+
+impl Pollable for Whiteout {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::ERR
+    }
+}
+
+impl FileIo for Whiteout {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "the whiteout device cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "the whiteout device cannot be written");
+    }
+}
@@ -2,9 +2,11 @@
 
 #![expect(dead_code)]
 
+use int_to_c_enum::TryFromInt;
+
 /// Error number.
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
 pub enum Errno {
     EPERM = 1,    /* Operation not permitted */
     ENOENT = 2,   /* No such file or directory */
@@ -152,6 +154,7 @@ pub enum Errno {
     EHWPOISON = 133, /* Memory page has hardware error */
 
     ERESTARTSYS = 512, /* Restart of an interrupted system call. For kernel internal use only. */
+    ERESTARTNOHAND = 514, /* Restart if no handler. For kernel internal use only. */
 }
 
 /// error used in this crate
@@ -241,11 +244,33 @@ impl From<aster_block::bio::BioStatus> for Error {
             aster_block::bio::BioStatus::IoError => {
                 Error::with_message(Errno::EIO, "I/O operation fails")
             }
+            aster_block::bio::BioStatus::TimedOut => {
+                Error::with_message(Errno::EIO, "I/O operation times out")
+            }
             status => panic!("Can not convert the status: {:?} to an error", status),
         }
     }
 }
 
+impl From<aster_block::partition::ScanError> for Error {
+    fn from(error: aster_block::partition::ScanError) -> Self {
+        match error {
+            aster_block::partition::ScanError::NotFound => {
+                Error::with_message(Errno::ENXIO, "The block device does not exist")
+            }
+            aster_block::partition::ScanError::IsPartition => {
+                Error::with_message(Errno::EINVAL, "The block device is a partition")
+            }
+            aster_block::partition::ScanError::Busy => {
+                Error::with_message(Errno::EBUSY, "The partitions are in use")
+            }
+            aster_block::partition::ScanError::IoError => {
+                Error::with_message(Errno::EIO, "The partition table cannot be read")
+            }
+        }
+    }
+}
+
 impl From<core::num::TryFromIntError> for Error {
     fn from(_: core::num::TryFromIntError) -> Self {
         Error::with_message(Errno::EINVAL, "Invalid integer")
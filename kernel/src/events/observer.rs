@@ -32,6 +32,25 @@ use super::Events;
 pub trait Observer<E: Events>: Send + Sync {
     /// Notify the observer that some interesting events happen.
     fn on_events(&self, events: &E);
+
+    /// Returns whether the observer is exclusive.
+    ///
+    /// When some events happen, all non-exclusive observers are notified, while
+    /// the exclusive observers are notified one by one only until one of them
+    /// accepts the events. This avoids waking up all the waiters when only one
+    /// of them can handle the events.
+    fn is_exclusive(&self) -> bool {
+        false
+    }
+
+    /// Notify the exclusive observer that some interesting events happen.
+    ///
+    /// This method returns whether the observer accepts the events. If it does
+    /// not, the events will be delivered to the next exclusive observer.
+    fn on_exclusive_events(&self, events: &E) -> bool {
+        self.on_events(events);
+        true
+    }
 }
 
 impl<E: Events> Observer<E> for () {
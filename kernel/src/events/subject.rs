@@ -97,10 +97,30 @@ impl<E: Events, F: EventsFilter<E>> Subject<E, F> {
         }
         drop(observers);
 
+        let mut exclusive_observers = Vec::new();
         for observer in active_observers {
-            observer.on_events(events);
+            if observer.is_exclusive() {
+                exclusive_observers.push(observer);
+            } else {
+                observer.on_events(events);
+            }
+        }
+
+        for observer in exclusive_observers {
+            if observer.on_exclusive_events(events) {
+                break;
+            }
         }
     }
+
+    /// Returns whether there are any registered observers.
+    ///
+    /// The result may be stale, since the observers may be registered or
+    /// unregistered concurrently, and the freed observers are counted until
+    /// they are removed lazily.
+    pub fn has_observers(&self) -> bool {
+        self.num_observers.load(Ordering::Relaxed) > 0
+    }
 }
 
 impl<E: Events> Default for Subject<E> {
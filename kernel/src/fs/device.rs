@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::{borrow::Cow, format};
+
+use aster_systree::{device_model, KObjectBuilder};
+
 use super::inode_handle::FileIo;
 use crate::{
     fs::{
-        fs_resolver::{FsPath, FsResolver},
+        devtmpfs,
         path::Dentry,
         utils::{InodeMode, InodeType},
     },
@@ -87,23 +91,41 @@ impl From<u64> for DeviceId {
     }
 }
 
-/// Add a device node to FS for the device.
+/// Add a device node to the devtmpfs for the device,
+/// and add the device to `class` in the device model of the sysfs.
 ///
 /// If the parent path is not existing, `mkdir -p` the parent path.
+/// The device model generates an `add` uevent for the device,
+/// so that a udev-like daemon in the user space can react to it.
 /// This function is used in registering device.
-pub fn add_node(device: Arc<dyn Device>, path: &str) -> Result<Dentry> {
-    let mut dentry = {
-        let fs_resolver = FsResolver::new();
-        fs_resolver.lookup(&FsPath::try_from("/dev").unwrap())?
-    };
-    let mut relative_path = {
-        let relative_path = path.trim_start_matches('/');
-        if relative_path.is_empty() {
-            return_errno_with_message!(Errno::EINVAL, "invalid device path");
-        }
-        relative_path
-    };
+pub fn add_node(device: Arc<dyn Device>, path: &str, class: &'static str) -> Result<Dentry> {
+    let dentry = add_devtmpfs_node(device.clone(), path)?;
+
+    // The device node is usable even if the device is missing in the sysfs.
+    let devname = path.trim_start_matches('/');
+    if let Err(err) = add_to_device_model(device.as_ref(), devname, class) {
+        warn!(
+            "failed to add device {} to the device model: {:?}",
+            devname, err
+        );
+    }
+
+    Ok(dentry)
+}
+
+/// Add a device node to the devtmpfs for the device, without adding the device to the device
+/// model.
+///
+/// This function is used for the devices that are added to the device model by their drivers
+/// (e.g., the block devices).
+pub fn add_devtmpfs_node(device: Arc<dyn Device>, path: &str) -> Result<Dentry> {
+    let devname = path.trim_start_matches('/');
+    if devname.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "invalid device path");
+    }
 
+    let mut dentry = devtmpfs::root()?.clone();
+    let mut relative_path = devname;
     while !relative_path.is_empty() {
         let (next_name, path_remain) = if let Some((prefix, suffix)) = relative_path.split_once('/')
         {
@@ -143,23 +165,75 @@ pub fn add_node(device: Arc<dyn Device>, path: &str) -> Result<Dentry> {
     Ok(dentry)
 }
 
-/// Delete the device node from FS for the device.
+/// Delete the device node from the devtmpfs for the device,
+/// and remove the device from `class` in the device model of the sysfs.
 ///
+/// The device model generates a `remove` uevent for the device.
 /// This function is used in unregistering device.
-pub fn delete_node(path: &str) -> Result<()> {
-    let abs_path = {
-        let device_path = path.trim_start_matches('/');
-        if device_path.is_empty() {
-            return_errno_with_message!(Errno::EINVAL, "invalid device path");
-        }
-        String::from("/dev") + "/" + device_path
-    };
+pub fn delete_node(path: &str, class: &'static str) -> Result<()> {
+    let devname = path.trim_start_matches('/');
+    if devname.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "invalid device path");
+    }
 
-    let (parent_dentry, name) = {
-        let fs_resolver = FsResolver::new();
-        fs_resolver.lookup_dir_and_base_name(&FsPath::try_from(abs_path.as_str()).unwrap())?
-    };
+    let model = device_model();
+    if let Err(err) = model
+        .virtual_class_dir(class)
+        .and_then(|parent| model.remove_device(&parent, &kobject_name(devname)))
+    {
+        warn!(
+            "failed to remove device {} from the device model: {:?}",
+            devname, err
+        );
+    }
+
+    delete_devtmpfs_node(devname)
+}
+
+/// Delete the device node from the devtmpfs, without removing the device from the device model.
+pub fn delete_devtmpfs_node(path: &str) -> Result<()> {
+    let devname = path.trim_start_matches('/');
+    if devname.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "invalid device path");
+    }
+
+    let (dir_path, name) = devname.rsplit_once('/').unwrap_or(("", devname));
+    let mut parent_dentry = devtmpfs::root()?.clone();
+    for dir_name in dir_path.split('/').filter(|dir_name| !dir_name.is_empty()) {
+        parent_dentry = parent_dentry.lookup(dir_name)?;
+    }
 
-    parent_dentry.unlink(&name)?;
+    parent_dentry.unlink(name)?;
     Ok(())
 }
+
+/// Adds a device to the `devices/virtual/<class>` directory of the device model.
+fn add_to_device_model(
+    device: &dyn Device,
+    devname: &str,
+    class: &'static str,
+) -> aster_systree::Result<()> {
+    let model = device_model();
+    model.register_class(class)?;
+    let parent = model.virtual_class_dir(class)?;
+
+    let id = device.id();
+    let mut builder = KObjectBuilder::new(Cow::Owned(kobject_name(devname)));
+    builder
+        .attr("dev", format!("{}:{}", id.major(), id.minor()))
+        .uevent(vec![
+            ("MAJOR", id.major().to_string()),
+            ("MINOR", id.minor().to_string()),
+            ("DEVNAME", devname.to_string()),
+        ]);
+    let kobj = builder.build(parent.as_ref())?;
+
+    model.add_device(&parent, kobj, None, Some(class))
+}
+
+/// Returns the name of the device in the device model.
+///
+/// The slashes in the device path are replaced with `!`, as in Linux (e.g., `net!tun`).
+fn kobject_name(devname: &str) -> String {
+    devname.replace('/', "!")
+}
@@ -13,13 +13,14 @@ use crate::{
     device::PtyMaster,
     fs::{
         device::{Device, DeviceId, DeviceType},
+        path::{Dentry, MountNode},
         utils::{
             DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, IoctlCmd, Metadata,
             SuperBlock, NAME_MAX,
         },
     },
     prelude::*,
-    process::{Gid, Uid},
+    process::{posix_thread::AsPosixThread, Gid, Uid},
 };
 
 mod ptmx;
@@ -41,23 +42,49 @@ const MAX_PTY_NUM: usize = 4096;
 /// represent slaves to the multiplexing master located at "/dev/ptmx".
 ///
 /// Actually, the "/dev/ptmx" is a symlink to the real device at "/dev/pts/ptmx".
+///
+/// Like Linux, each mount of devpts is an independent instance, which has its own ptmx and its
+/// own set of pty indexes. So a container can mount a private devpts instance without seeing or
+/// exhausting the pty pairs of others.
 pub struct DevPts {
     sb: SuperBlock,
     root: Arc<RootInode>,
     index_alloc: Mutex<IdAlloc>,
+    options: DevPtsOptions,
+    /// The mount where the instance is mounted, which is used to open the slaves
+    mount_node: RwLock<Weak<MountNode>>,
     this: Weak<Self>,
 }
 
 impl DevPts {
     pub fn new() -> Arc<Self> {
+        Self::with_options(DevPtsOptions::default())
+    }
+
+    pub fn with_options(options: DevPtsOptions) -> Arc<Self> {
         Arc::new_cyclic(|weak_self| Self {
             sb: SuperBlock::new(DEVPTS_MAGIC, BLOCK_SIZE, NAME_MAX),
-            root: RootInode::new(weak_self.clone()),
+            root: RootInode::new(weak_self.clone(), options.ptmx_mode),
             index_alloc: Mutex::new(IdAlloc::with_capacity(MAX_PTY_NUM)),
+            options,
+            mount_node: RwLock::new(Weak::new()),
             this: weak_self.clone(),
         })
     }
 
+    /// Records the mount where the instance is mounted.
+    pub fn set_mount_node(&self, mount_node: &Arc<MountNode>) {
+        *self.mount_node.write() = Arc::downgrade(mount_node);
+    }
+
+    /// Returns the dentry of the slave with the index.
+    pub fn slave_dentry(&self, index: u32) -> Result<Dentry> {
+        let Some(mount_node) = self.mount_node.read().upgrade() else {
+            return_errno_with_message!(Errno::EIO, "the devpts instance is not mounted");
+        };
+        Dentry::new_fs_root(mount_node).lookup(&index.to_string())
+    }
+
     /// Create the master and slave pair.
     fn create_master_slave_pair(&self) -> Result<(Arc<PtyMaster>, Arc<PtySlaveInode>)> {
         let index = self
@@ -65,10 +92,17 @@ impl DevPts {
             .lock()
             .alloc()
             .ok_or_else(|| Error::with_message(Errno::EIO, "cannot alloc index"))?;
+        if index >= self.options.max {
+            self.index_alloc.lock().free(index);
+            return_errno_with_message!(Errno::ENOSPC, "the pty pairs are exhausted");
+        }
 
         let (master, slave) = crate::device::new_pty_pair(index as u32, self.root.ptmx.clone())?;
 
-        let slave_inode = PtySlaveInode::new(slave, self.this.clone());
+        let slave_inode = PtySlaveInode::new(slave, self.options.mode, self.this.clone());
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        slave_inode.set_owner(self.options.uid.unwrap_or(credentials.fsuid()))?;
+        slave_inode.set_group(self.options.gid.unwrap_or(credentials.fsgid()))?;
         self.root.add_slave(index.to_string(), slave_inode.clone());
 
         Ok((master, slave_inode))
@@ -104,6 +138,66 @@ impl FileSystem for DevPts {
     }
 }
 
+/// The mount options of devpts.
+#[derive(Debug, Clone, Copy)]
+pub struct DevPtsOptions {
+    /// The owner of the slaves, or the creator if it is `None`.
+    uid: Option<Uid>,
+    /// The group of the slaves, or the group of the creator if it is `None`.
+    gid: Option<Gid>,
+    /// The mode of the slaves.
+    mode: InodeMode,
+    /// The mode of the ptmx.
+    ptmx_mode: InodeMode,
+    /// The max number of pty pairs.
+    max: usize,
+}
+
+impl Default for DevPtsOptions {
+    fn default() -> Self {
+        Self {
+            uid: None,
+            gid: None,
+            mode: InodeMode::from_bits_truncate(0o620),
+            ptmx_mode: InodeMode::from_bits_truncate(0o666),
+            max: MAX_PTY_NUM,
+        }
+    }
+}
+
+impl DevPtsOptions {
+    /// Parses the options from the mount data, e.g., `gid=5,mode=620,ptmxmode=666`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let parse_num = |radix| {
+                u32::from_str_radix(value, radix).map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the devpts mount option is invalid")
+                })
+            };
+
+            match key {
+                "uid" => options.uid = Some(Uid::new(parse_num(10)?)),
+                "gid" => options.gid = Some(Gid::new(parse_num(10)?)),
+                "mode" => options.mode = InodeMode::from_bits_truncate(parse_num(8)? as u16),
+                "ptmxmode" => {
+                    options.ptmx_mode = InodeMode::from_bits_truncate(parse_num(8)? as u16)
+                }
+                "max" => options.max = (parse_num(10)? as usize).min(MAX_PTY_NUM),
+                // Every mount is a new instance, so the option is meaningless.
+                "newinstance" => (),
+                _ => {
+                    return_errno_with_message!(Errno::EINVAL, "the devpts mount option is unknown")
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
 struct RootInode {
     ptmx: Arc<Ptmx>,
     slaves: RwLock<SlotVec<(String, Arc<PtySlaveInode>)>>,
@@ -112,9 +206,9 @@ struct RootInode {
 }
 
 impl RootInode {
-    pub fn new(fs: Weak<DevPts>) -> Arc<Self> {
+    pub fn new(fs: Weak<DevPts>, ptmx_mode: InodeMode) -> Arc<Self> {
         Arc::new(Self {
-            ptmx: Ptmx::new(fs.clone()),
+            ptmx: Ptmx::new(fs.clone(), ptmx_mode),
             slaves: RwLock::new(SlotVec::new()),
             metadata: RwLock::new(Metadata::new_dir(
                 ROOT_INO,
@@ -22,11 +22,11 @@ pub struct PtySlaveInode {
 }
 
 impl PtySlaveInode {
-    pub fn new(device: Arc<PtySlave>, fs: Weak<DevPts>) -> Arc<Self> {
+    pub fn new(device: Arc<PtySlave>, mode: InodeMode, fs: Weak<DevPts>) -> Arc<Self> {
         Arc::new(Self {
             metadata: RwLock::new(Metadata::new_device(
                 device.index() as u64 + FIRST_SLAVE_INO,
-                InodeMode::from_bits_truncate(0o620),
+                mode,
                 super::BLOCK_SIZE,
                 device.as_ref(),
             )),
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device file system (devtmpfs).
+//!
+//! The devtmpfs is a RamFS instance in which the device nodes are created and removed
+//! by the kernel as the devices come and go (see [`super::device::add_node`]),
+//! so that `/dev` is populated without the help of the user space.
+//! The user space can still create additional nodes and symlinks in it.
+//!
+//! The devtmpfs is mounted at `/dev` during boot.
+//! Mounting it elsewhere (e.g., `mount -t devtmpfs devtmpfs /mnt`) shows the same nodes.
+
+use spin::Once;
+
+use super::{path::Dentry, ramfs::RamFS};
+use crate::prelude::*;
+
+static DEVTMPFS: Once<Arc<RamFS>> = Once::new();
+
+/// The root directory of the devtmpfs in its first mount.
+static ROOT: Once<Dentry> = Once::new();
+
+/// Returns the devtmpfs instance.
+pub fn singleton() -> &'static Arc<RamFS> {
+    DEVTMPFS.call_once(RamFS::new)
+}
+
+/// Mounts the devtmpfs at `mountpoint` for the first time.
+pub(super) fn init(mountpoint: &Dentry) -> Result<()> {
+    let mount_node = mountpoint.mount(singleton().clone())?;
+    ROOT.call_once(|| Dentry::new_fs_root(mount_node));
+    Ok(())
+}
+
+/// Returns the root directory of the devtmpfs,
+/// in which the device nodes are created.
+pub(super) fn root() -> Result<&'static Dentry> {
+    ROOT.get()
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the devtmpfs is not mounted"))
+}
@@ -148,6 +148,9 @@ impl Entry {
         inner.event = event;
         inner.flags = flags;
 
+        self.observer
+            .is_exclusive
+            .store(flags.contains(EpollFlags::EXCLUSIVE), Ordering::Relaxed);
         self.observer.set_enabled(&inner);
 
         file.poll(event.events, Some(&mut inner.poller))
@@ -163,6 +166,11 @@ impl Entry {
         inner.poller.reset();
     }
 
+    /// Returns whether the epoll entry is exclusive (i.e., has [`EpollFlags::EXCLUSIVE`]).
+    pub(super) fn is_exclusive(&self) -> bool {
+        self.observer.is_exclusive.load(Ordering::Relaxed)
+    }
+
     /// Gets the underlying observer.
     pub(super) fn observer(&self) -> &Observer {
         &self.observer
@@ -190,6 +198,8 @@ pub(super) struct Observer {
     is_enabled: AtomicBool,
     // Whether the entry is in the ready list.
     is_ready: AtomicBool,
+    // Whether the entry is exclusive.
+    is_exclusive: AtomicBool,
     // The ready set of the epoll file that contains this epoll entry.
     ready_set: Arc<ReadySet>,
     // The epoll entry itself (always inside an `Arc`).
@@ -201,6 +211,7 @@ impl Observer {
         Self {
             is_enabled: AtomicBool::new(false),
             is_ready: AtomicBool::new(false),
+            is_exclusive: AtomicBool::new(false),
             ready_set,
             weak_entry,
         }
@@ -269,6 +280,16 @@ impl events::Observer<IoEvents> for Observer {
     fn on_events(&self, _events: &IoEvents) {
         self.ready_set.push(self);
     }
+
+    fn is_exclusive(&self) -> bool {
+        self.is_exclusive.load(Ordering::Relaxed)
+    }
+
+    fn on_exclusive_events(&self, _events: &IoEvents) -> bool {
+        // The events are accepted only if someone is waiting on the epoll file to handle them.
+        // Otherwise, they should be delivered to other epoll files.
+        self.ready_set.push(self) && self.ready_set.has_waiters()
+    }
 }
 
 /// A set of ready epoll entries.
@@ -292,7 +313,10 @@ impl ReadySet {
         }
     }
 
-    pub(super) fn push(&self, observer: &Observer) {
+    /// Pushes the epoll entry to the ready list.
+    ///
+    /// This method returns `false` if the entry is disabled and thus not pushed.
+    pub(super) fn push(&self, observer: &Observer) -> bool {
         // Note that we cannot take the `Inner` lock because we may be in the callback of the event
         // observer. Doing so will cause dead locks due to inconsistent locking orders.
         //
@@ -302,7 +326,7 @@ impl ReadySet {
         // - Catching spurious events here is always fine because we always check them later before
         //   returning events to the user (in `Entry::poll`).
         if !observer.is_enabled() {
-            return;
+            return false;
         }
 
         let mut entries = self.entries.lock();
@@ -316,6 +340,13 @@ impl ReadySet {
         // there might be new events that we are interested in.
         // Wake the poller anyway.
         self.pollee.notify(IoEvents::IN);
+
+        true
+    }
+
+    /// Returns whether someone is waiting for the ready entries.
+    fn has_waiters(&self) -> bool {
+        self.pollee.has_pollers()
     }
 
     pub(super) fn lock_pop(&self) -> ReadySetPopIter {
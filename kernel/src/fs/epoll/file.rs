@@ -24,6 +24,16 @@ use crate::{
     },
 };
 
+/// The events that can be monitored by exclusive entries.
+const EXCLUSIVE_EVENTS: IoEvents = IoEvents::IN
+    .union(IoEvents::OUT)
+    .union(IoEvents::ERR)
+    .union(IoEvents::HUP);
+/// The flags that can be used along with [`EpollFlags::EXCLUSIVE`].
+const EXCLUSIVE_FLAGS: EpollFlags = EpollFlags::EXCLUSIVE
+    .union(EpollFlags::WAKE_UP)
+    .union(EpollFlags::EDGE_TRIGGER);
+
 /// A file-like object that provides epoll API.
 ///
 /// Conceptually, we maintain two lists: one consists of all interesting files,
@@ -87,6 +97,23 @@ impl EpollFile {
     ) -> Result<()> {
         self.warn_unsupported_flags(&ep_flags);
 
+        if ep_flags.contains(EpollFlags::EXCLUSIVE) {
+            if !(ep_event.events - EXCLUSIVE_EVENTS).is_empty()
+                || !(ep_flags - EXCLUSIVE_FLAGS).is_empty()
+            {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the events or flags are not allowed for exclusive entries"
+                );
+            }
+            if file.downcast_ref::<EpollFile>().is_some() {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "epoll files cannot be added as exclusive entries"
+                );
+            }
+        }
+
         // Add the new entry to the interest list and start monitoring its events
         let ready_entry = {
             let mut interest = self.interest.lock();
@@ -147,6 +174,10 @@ impl EpollFile {
     ) -> Result<()> {
         self.warn_unsupported_flags(&new_ep_flags);
 
+        if new_ep_flags.contains(EpollFlags::EXCLUSIVE) {
+            return_errno_with_message!(Errno::EINVAL, "exclusive entries cannot be modified");
+        }
+
         // Update the epoll entry
         let ready_entry = {
             let interest = self.interest.lock();
@@ -155,6 +186,9 @@ impl EpollFile {
                 interest.get(&EntryKey::from((fd, &file))).ok_or_else(|| {
                     Error::with_message(Errno::ENOENT, "the file is not in the interest list")
                 })?;
+            if entry.is_exclusive() {
+                return_errno_with_message!(Errno::EINVAL, "exclusive entries cannot be modified");
+            }
             let events = entry.update(new_ep_event, new_ep_flags);
 
             if !events.is_empty() {
@@ -240,7 +274,7 @@ impl EpollFile {
     }
 
     fn warn_unsupported_flags(&self, flags: &EpollFlags) {
-        if flags.intersects(EpollFlags::EXCLUSIVE | EpollFlags::WAKE_UP) {
+        if flags.intersects(EpollFlags::WAKE_UP) {
             warn!("{:?} contains unsupported flags", flags);
         }
     }
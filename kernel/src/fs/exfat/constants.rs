@@ -11,6 +11,7 @@ pub(super) const MAX_VFSNAME_BUF_SIZE: usize = (MAX_NAME_LENGTH + 1) * MAX_CHARS
 pub(super) const BOOT_SIGNATURE: u16 = 0xAA55;
 pub(super) const EXBOOT_SIGNATURE: u32 = 0xAA550000;
 pub(super) const STR_EXFAT: &str = "EXFAT   "; // size should be 8
+pub(super) const EXFAT_MAGIC: u64 = 0x2011_BAB0;
 
 pub(super) const VOLUME_DIRTY: u16 = 0x0002;
 pub(super) const MEDIA_FAILURE: u16 = 0x0004;
@@ -415,7 +415,11 @@ impl FileSystem for ExfatFS {
     }
 
     fn sb(&self) -> SuperBlock {
-        SuperBlock::new(BOOT_SIGNATURE as u64, self.sector_size(), MAX_NAME_LENGTH)
+        let mut sb = SuperBlock::new(EXFAT_MAGIC, self.cluster_size(), MAX_NAME_LENGTH);
+        sb.blocks = (self.super_block.num_clusters - EXFAT_RESERVED_CLUSTERS) as usize;
+        sb.bfree = self.num_free_clusters() as usize;
+        sb.bavail = sb.bfree;
+        sb
     }
 
     fn flags(&self) -> FsFlags {
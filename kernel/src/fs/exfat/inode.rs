@@ -31,8 +31,8 @@ use crate::{
         exfat::{dentry::ExfatDentryIterator, fat::ExfatChain, fs::ExfatFS},
         path::{is_dot, is_dot_or_dotdot, is_dotdot},
         utils::{
-            CachePage, DirentVisitor, Extension, Inode, InodeMode, InodeType, IoctlCmd, Metadata,
-            MknodType, PageCache, PageCacheBackend,
+            CachePage, DirentVisitor, ExtendedMetadata, Extension, Inode, InodeMode, InodeType,
+            IoctlCmd, Metadata, MknodType, PageCache, PageCacheBackend,
         },
     },
     prelude::*,
@@ -111,7 +111,9 @@ struct ExfatInodeInner {
     /// Modification time, updated only on write.
     mtime: DosTimestamp,
     /// Creation time.
-    ctime: DosTimestamp,
+    crtime: DosTimestamp,
+    /// Status change time, which is not stored on disk.
+    ctime: Duration,
 
     /// Number of sub inodes.
     num_sub_inodes: u32,
@@ -321,10 +323,10 @@ impl ExfatInodeInner {
 
         file_dentry.attribute = self.attr.bits();
 
-        file_dentry.create_utc_offset = self.ctime.utc_offset;
-        file_dentry.create_date = self.ctime.date;
-        file_dentry.create_time = self.ctime.time;
-        file_dentry.create_time_cs = self.ctime.increment_10ms;
+        file_dentry.create_utc_offset = self.crtime.utc_offset;
+        file_dentry.create_date = self.crtime.date;
+        file_dentry.create_time = self.crtime.time;
+        file_dentry.create_time_cs = self.crtime.increment_10ms;
 
         file_dentry.modify_utc_offset = self.mtime.utc_offset;
         file_dentry.modify_date = self.mtime.date;
@@ -611,6 +613,7 @@ impl ExfatInodeInner {
         let now = DosTimestamp::now()?;
         self.atime = now;
         self.mtime = now;
+        self.ctime = now.as_duration().unwrap_or_default();
         Ok(())
     }
 }
@@ -648,7 +651,7 @@ impl ExfatInode {
 
         let inode_type = InodeType::Dir;
 
-        let ctime = DosTimestamp::now()?;
+        let crtime = DosTimestamp::now()?;
 
         let size = root_chain.num_clusters() as usize * sb.cluster_size as usize;
 
@@ -665,9 +668,10 @@ impl ExfatInode {
                 start_chain: root_chain,
                 size,
                 size_allocated: size,
-                atime: ctime,
-                mtime: ctime,
-                ctime,
+                atime: crtime,
+                mtime: crtime,
+                crtime,
+                ctime: crtime.as_duration().unwrap_or_default(),
                 num_sub_inodes: 0,
                 num_sub_dirs: 0,
                 name,
@@ -722,7 +726,7 @@ impl ExfatInode {
             InodeType::File
         };
 
-        let ctime = DosTimestamp::new(
+        let crtime = DosTimestamp::new(
             file.create_time,
             file.create_date,
             file.create_time_cs,
@@ -777,7 +781,9 @@ impl ExfatInode {
                 size_allocated,
                 atime,
                 mtime,
-                ctime,
+                crtime,
+                // Like Linux, the status change time is initialized with the modification time.
+                ctime: mtime.as_duration().unwrap_or_default(),
                 num_sub_inodes: 0,
                 num_sub_dirs: 0,
                 name,
@@ -995,6 +1001,7 @@ impl ExfatInode {
         self_inner.dentry_set_size = other_inner.dentry_set_size;
         self_inner.dentry_entry = other_inner.dentry_entry;
         self_inner.atime = other_inner.atime;
+        self_inner.crtime = other_inner.crtime;
         self_inner.ctime = other_inner.ctime;
         self_inner.mtime = other_inner.mtime;
         self_inner.name = other_inner.name.clone();
@@ -1151,7 +1158,7 @@ impl Inode for ExfatInode {
             blocks: inner.size.div_ceil(blk_size),
             atime: inner.atime.as_duration().unwrap_or_default(),
             mtime: inner.mtime.as_duration().unwrap_or_default(),
-            ctime: inner.ctime.as_duration().unwrap_or_default(),
+            ctime: inner.ctime,
             type_: inner.inode_type,
             mode: inner.make_mode(),
             nlinks,
@@ -1162,6 +1169,13 @@ impl Inode for ExfatInode {
         }
     }
 
+    fn extended_metadata(&self) -> ExtendedMetadata {
+        ExtendedMetadata {
+            btime: self.inner.read().crtime.as_duration().ok(),
+            ..Default::default()
+        }
+    }
+
     fn type_(&self) -> InodeType {
         self.inner.read().inode_type
     }
@@ -1192,11 +1206,11 @@ impl Inode for ExfatInode {
     }
 
     fn ctime(&self) -> Duration {
-        self.inner.read().ctime.as_duration().unwrap_or_default()
+        self.inner.read().ctime
     }
 
     fn set_ctime(&self, time: Duration) {
-        self.inner.write().ctime = DosTimestamp::from_duration(time).unwrap_or_default();
+        self.inner.write().ctime = time;
     }
 
     fn owner(&self) -> Result<Uid> {
@@ -1242,7 +1256,6 @@ impl Inode for ExfatInode {
         };
         inner.page_cache.pages().read(read_off, writer)?;
 
-        inner.upgrade().update_atime()?;
         Ok(read_len)
     }
 
@@ -1295,7 +1308,6 @@ impl Inode for ExfatInode {
             }
         }
 
-        inner.upgrade().update_atime()?;
         Ok(read_len)
     }
 
@@ -1502,8 +1514,6 @@ impl Inode for ExfatInode {
             dir_read
         };
 
-        inner.upgrade().update_atime()?;
-
         Ok(dir_read)
     }
 
@@ -33,7 +33,10 @@ mod test {
                 constants::{EXFAT_RESERVED_CLUSTERS, MAX_NAME_LENGTH},
                 ExfatFS, ExfatMountOptions,
             },
-            utils::{generate_random_operation, new_fs_in_memory, Inode, InodeMode, InodeType},
+            utils::{
+                check_crash_consistency, generate_random_operation, new_fs_in_memory,
+                run_benchmarks, FileSystem, FsExerciser, Inode, InodeMode, InodeType, MemoryDisk,
+            },
         },
         prelude::*,
     };
@@ -1076,4 +1079,30 @@ mod test {
             file_or_dir.execute_and_test(op, &mut rng);
         }
     }
+
+    #[ktest]
+    fn exerciser() {
+        let fs = load_exfat();
+        let root: Arc<dyn Inode> = fs.root_inode();
+        FsExerciser::new(&root, 0).run(500);
+    }
+
+    #[ktest]
+    fn crash_consistency() {
+        check_crash_consistency(
+            MemoryDisk::from_image(EXFAT_IMAGE),
+            |disk| -> Arc<dyn FileSystem> {
+                ExfatFS::open(disk, ExfatMountOptions::default()).unwrap()
+            },
+            0,
+            200,
+        );
+    }
+
+    #[ktest]
+    fn benchmarks() {
+        let fs = load_exfat();
+        let root: Arc<dyn Inode> = fs.root_inode();
+        run_benchmarks(&root);
+    }
 }
@@ -42,7 +42,7 @@ impl BlockGroup {
                 let descriptor = {
                     // Read the block group descriptor
                     // TODO: if the main is corrupted, should we load the backup?
-                    let offset = idx * core::mem::size_of::<RawGroupDescriptor>();
+                    let offset = idx * super_block.desc_size();
                     let raw_descriptor = group_descriptors_segment
                         .read_val::<RawGroupDescriptor>(offset)
                         .unwrap();
@@ -58,19 +58,30 @@ impl BlockGroup {
                     Ok(IdAlloc::from_bytes_with_capacity(&buf, capacity))
                 };
 
-                let block_bitmap = get_bitmap(
-                    descriptor.block_bitmap_bid,
-                    super_block.blocks_per_group() as usize,
-                )?;
-                let inode_bitmap = get_bitmap(
-                    descriptor.inode_bitmap_bid,
-                    super_block.inodes_per_group() as usize,
-                )?;
+                // The bitmaps of uninitialized groups are not initialized on the device. They
+                // are computed here, and written back with the flags cleared when syncing.
+                let block_bitmap = if descriptor.flags.contains(GroupFlags::BLOCK_UNINIT) {
+                    init_block_bitmap(idx, &descriptor, super_block)
+                } else {
+                    get_bitmap(
+                        descriptor.block_bitmap_bid,
+                        super_block.blocks_per_group() as usize,
+                    )?
+                };
+                let inode_bitmap = if descriptor.flags.contains(GroupFlags::INODE_UNINIT) {
+                    IdAlloc::with_capacity(super_block.inodes_per_group() as usize)
+                } else {
+                    get_bitmap(
+                        descriptor.inode_bitmap_bid,
+                        super_block.inodes_per_group() as usize,
+                    )?
+                };
 
                 GroupMetadata {
                     descriptor,
                     block_bitmap,
                     inode_bitmap,
+                    inodes_per_group: super_block.inodes_per_group(),
                 }
             };
 
@@ -134,7 +145,11 @@ impl BlockGroup {
                 .read_val::<RawInode>(offset)
                 .unwrap()
         };
-        let inode_desc = Dirty::new(InodeDesc::try_from(raw_inode)?);
+        let inode_desc = {
+            let mut inode_desc = InodeDesc::try_from(raw_inode)?;
+            inode_desc.set_project_id(self.read_project_id(inode_idx));
+            Dirty::new(inode_desc)
+        };
         let ino = inode_idx + self.idx as u32 * fs.inodes_per_group() + 1;
 
         Ok(Inode::new(ino, self.idx, inode_desc, Arc::downgrade(&fs)))
@@ -218,6 +233,81 @@ impl BlockGroup {
             .unwrap();
     }
 
+    /// Initializes the raw inode metadata of a new inode.
+    ///
+    /// The inode table of an uninitialized group may contain garbage, so the whole slot is
+    /// zeroed, including the extra fields and the extended attributes following the
+    /// 128-byte raw inode.
+    pub fn init_raw_inode(&self, inode_idx: u32, extra_isize: u16) {
+        let inode_size = self.fs().inode_size();
+        let mut buf = vec![0u8; inode_size];
+        if inode_size > core::mem::size_of::<RawInode>() {
+            buf[EXTRA_ISIZE_OFFSET..EXTRA_ISIZE_OFFSET + 2]
+                .copy_from_slice(&extra_isize.to_le_bytes());
+        }
+        self.raw_inodes_cache
+            .pages()
+            .write_bytes(inode_idx as usize * inode_size, &buf)
+            .unwrap();
+    }
+
+    /// Reads the bytes at `offset` after the 128-byte raw inode.
+    pub fn read_raw_inode_extra(&self, inode_idx: u32, offset: usize, buf: &mut [u8]) {
+        let inode_size = self.fs().inode_size();
+        debug_assert!(core::mem::size_of::<RawInode>() + offset + buf.len() <= inode_size);
+        let offset = (inode_idx as usize) * inode_size + core::mem::size_of::<RawInode>() + offset;
+        self.raw_inodes_cache
+            .pages()
+            .read_bytes(offset, buf)
+            .unwrap();
+    }
+
+    /// Writes the bytes at `offset` after the 128-byte raw inode.
+    pub fn write_raw_inode_extra(&self, inode_idx: u32, offset: usize, buf: &[u8]) {
+        let inode_size = self.fs().inode_size();
+        debug_assert!(core::mem::size_of::<RawInode>() + offset + buf.len() <= inode_size);
+        let offset = (inode_idx as usize) * inode_size + core::mem::size_of::<RawInode>() + offset;
+        self.raw_inodes_cache
+            .pages()
+            .write_bytes(offset, buf)
+            .unwrap();
+    }
+
+    /// Reads the project ID of the inode.
+    ///
+    /// The project ID is zero if the extra fields of the inode do not cover it.
+    pub fn read_project_id(&self, inode_idx: u32) -> u32 {
+        if !self.has_project_id(inode_idx) {
+            return 0;
+        }
+
+        let mut project_id = [0u8; 4];
+        self.read_raw_inode_extra(inode_idx, PROJECT_ID_OFFSET, &mut project_id);
+        u32::from_le_bytes(project_id)
+    }
+
+    /// Writes the project ID of the inode.
+    ///
+    /// Nothing is written if the extra fields of the inode do not cover the project ID.
+    pub fn write_project_id(&self, inode_idx: u32, project_id: u32) {
+        if !self.has_project_id(inode_idx) {
+            return;
+        }
+
+        self.write_raw_inode_extra(inode_idx, PROJECT_ID_OFFSET, &project_id.to_le_bytes());
+    }
+
+    fn has_project_id(&self, inode_idx: u32) -> bool {
+        const PROJECT_ID_END: usize = PROJECT_ID_OFFSET + core::mem::size_of::<u32>();
+
+        if self.fs().inode_size() < core::mem::size_of::<RawInode>() + PROJECT_ID_END {
+            return false;
+        }
+        let mut extra_isize = [0u8; 2];
+        self.read_raw_inode_extra(inode_idx, 0, &mut extra_isize);
+        u16::from_le_bytes(extra_isize) as usize >= PROJECT_ID_END
+    }
+
     /// Writes back the metadata of this group.
     pub fn sync_metadata(&self) -> Result<()> {
         if !self.bg_impl.inner.read().metadata.is_dirty() {
@@ -226,21 +316,27 @@ impl BlockGroup {
 
         let mut inner = self.bg_impl.inner.write();
         let fs = self.fs();
-        // Writes back the descriptor.
+        // Writes back the descriptor. The bitmaps are written back below, so they are no
+        // longer uninitialized.
+        inner
+            .metadata
+            .descriptor
+            .flags
+            .remove(GroupFlags::BLOCK_UNINIT | GroupFlags::INODE_UNINIT);
         let raw_descriptor = RawGroupDescriptor::from(&inner.metadata.descriptor);
         self.fs().sync_group_descriptor(self.idx, &raw_descriptor)?;
 
         let mut bio_waiter = BioWaiter::new();
         // Writes back the inode bitmap.
         let inode_bitmap_bid = Bid::new(inner.metadata.descriptor.inode_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
+        bio_waiter.concat(fs.write_metadata_bytes_async(
             inode_bitmap_bid.to_offset(),
             inner.metadata.inode_bitmap.as_bytes(),
         )?);
 
         // Writes back the block bitmap.
         let block_bitmap_bid = Bid::new(inner.metadata.descriptor.block_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
+        bio_waiter.concat(fs.write_metadata_bytes_async(
             block_bitmap_bid.to_offset(),
             inner.metadata.block_bitmap.as_bytes(),
         )?);
@@ -344,7 +440,7 @@ impl PageCacheBackend for BlockGroupImpl {
         self.fs
             .upgrade()
             .unwrap()
-            .write_blocks_async(bid, bio_segment)
+            .write_metadata_async(bid, bio_segment)
     }
 
     fn npages(&self) -> usize {
@@ -363,6 +459,7 @@ struct GroupMetadata {
     descriptor: GroupDescriptor,
     block_bitmap: IdAlloc,
     inode_bitmap: IdAlloc,
+    inodes_per_group: u32,
 }
 
 impl GroupMetadata {
@@ -371,12 +468,18 @@ impl GroupMetadata {
     }
 
     pub fn alloc_inode(&mut self, is_dir: bool) -> Option<u32> {
-        let inode_idx = self.inode_bitmap.alloc()?;
+        let inode_idx = self.inode_bitmap.alloc()? as u32;
         self.dec_free_inodes();
         if is_dir {
             self.inc_dirs();
         }
-        Some(inode_idx as u32)
+        // The inodes at the end of the inode table that have never been used can be skipped
+        // by e2fsck, so the new inode must not be among them.
+        let unused_start = self.inodes_per_group - self.descriptor.itable_unused as u32;
+        if inode_idx >= unused_start {
+            self.descriptor.itable_unused = (self.inodes_per_group - inode_idx - 1) as u16;
+        }
+        Some(inode_idx)
     }
 
     pub fn free_inode(&mut self, inode_idx: u32, is_dir: bool) {
@@ -472,6 +575,14 @@ struct GroupDescriptor {
     free_inodes_count: u16,
     /// Number of directories in group
     dirs_count: u16,
+    /// Block group flags
+    flags: GroupFlags,
+    /// Number of unused inodes at the end of the inode table
+    itable_unused: u16,
+    /// The raw descriptor loaded from the device.
+    ///
+    /// The fields that are not tracked above are written back as is.
+    raw: RawGroupDescriptor,
 }
 
 impl From<RawGroupDescriptor> for GroupDescriptor {
@@ -483,10 +594,103 @@ impl From<RawGroupDescriptor> for GroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
+            flags: GroupFlags::from_bits_truncate(desc.flags),
+            itable_unused: desc.itable_unused,
+            raw: desc,
+        }
+    }
+}
+
+bitflags! {
+    /// The flags of block groups.
+    struct GroupFlags: u16 {
+        /// The inode table and the inode bitmap are not initialized.
+        const INODE_UNINIT = 1 << 0;
+        /// The block bitmap is not initialized.
+        const BLOCK_UNINIT = 1 << 1;
+        /// The inode table is zeroed.
+        const ITABLE_ZEROED = 1 << 2;
+    }
+}
+
+/// Computes the block bitmap of a group whose block bitmap is not initialized.
+///
+/// Such a group contains no data blocks, so only the metadata blocks are in use.
+fn init_block_bitmap(
+    idx: usize,
+    descriptor: &GroupDescriptor,
+    super_block: &SuperBlock,
+) -> IdAlloc {
+    let blocks_per_group = super_block.blocks_per_group();
+    let mut bitmap = IdAlloc::with_capacity(blocks_per_group as usize);
+    let group_start = idx as Ext2Bid * blocks_per_group;
+    let group_range = group_start..group_start + blocks_per_group;
+    let mut mark_in_use = |range: Range<Ext2Bid>| {
+        for bid in range {
+            if group_range.contains(&bid) {
+                bitmap.alloc_specific((bid - group_start) as usize);
+            }
         }
+    };
+
+    // The superblock, the group descriptor table and the reserved blocks for growing it.
+    if super_block.has_super_block(idx) {
+        let end = super_block.group_descriptors_bid(idx).to_raw() as Ext2Bid
+            + super_block.group_descriptors_blocks() as Ext2Bid
+            + super_block.reserved_gdt_blocks();
+        mark_in_use(group_start..end);
     }
+
+    // The bitmaps and the inode table, which may be located in other groups with `FLEX_BG`.
+    let inode_table_blocks = (super_block.inodes_per_group() as usize * super_block.inode_size())
+        .div_ceil(BLOCK_SIZE) as Ext2Bid;
+    mark_in_use(descriptor.block_bitmap_bid..descriptor.block_bitmap_bid + 1);
+    mark_in_use(descriptor.inode_bitmap_bid..descriptor.inode_bitmap_bid + 1);
+    mark_in_use(descriptor.inode_table_bid..descriptor.inode_table_bid + inode_table_blocks);
+
+    // The blocks beyond the end of the filesystem in the last group.
+    mark_in_use(super_block.total_blocks()..group_range.end);
+
+    bitmap
+}
+
+/// Computes the checksum of a raw group descriptor with the `GDT_CSUM` feature.
+///
+/// `raw_descriptor` contains all the `desc_size` bytes of the descriptor.
+pub(super) fn group_descriptor_checksum(
+    uuid: &[u8; 16],
+    block_group_idx: usize,
+    raw_descriptor: &[u8],
+) -> u16 {
+    const CHECKSUM_OFFSET: usize = 0x1E;
+
+    let mut crc = crc16(!0, uuid);
+    crc = crc16(crc, &(block_group_idx as u32).to_le_bytes());
+    crc = crc16(crc, &raw_descriptor[..CHECKSUM_OFFSET]);
+    crc16(crc, &raw_descriptor[CHECKSUM_OFFSET + 2..])
 }
 
+/// Computes the CRC-16 with the polynomial `0x8005` in the bit-reversed form.
+fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    for byte in bytes {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// The offset of the `i_extra_isize` field in the raw inode.
+const EXTRA_ISIZE_OFFSET: usize = 128;
+
+/// The offset of the `i_projid` field after the 128-byte raw inode.
+const PROJECT_ID_OFFSET: usize = 0x9c - 128;
+
 const_assert!(core::mem::size_of::<RawGroupDescriptor>() == 32);
 
 /// The raw block group descriptor.
@@ -501,8 +705,12 @@ pub(super) struct RawGroupDescriptor {
     pub free_blocks_count: u16,
     pub free_inodes_count: u16,
     pub dirs_count: u16,
-    pad: u16,
-    reserved: [u32; 3],
+    pub flags: u16,
+    exclude_bitmap: u32,
+    block_bitmap_csum: u16,
+    inode_bitmap_csum: u16,
+    pub itable_unused: u16,
+    pub checksum: u16,
 }
 
 impl From<&GroupDescriptor> for RawGroupDescriptor {
@@ -514,8 +722,9 @@ impl From<&GroupDescriptor> for RawGroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
-            pad: 0u16,
-            reserved: [0u32; 3],
+            flags: desc.flags.bits(),
+            itable_unused: desc.itable_unused,
+            ..desc.raw
         }
     }
 }
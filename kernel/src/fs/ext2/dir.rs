@@ -158,9 +158,14 @@ pub struct DirEntryReader<'a> {
 
 /// An iterator for iterating `DirEntryItem` from the
 /// page cache given a start offset.
+///
+/// The empty entries, whose inode numbers are zero, are skipped unless `includes_empty`
+/// is true. Such entries are left by Linux when removing the first entry of a block, and
+/// are used to hide the index nodes of hash-indexed directories.
 pub(super) struct DirEntryIter<'a> {
     page_cache: &'a PageCache,
     offset: usize,
+    includes_empty: bool,
 }
 
 impl<'a> DirEntryReader<'a> {
@@ -178,50 +183,71 @@ impl<'a> DirEntryReader<'a> {
         DirEntryIter {
             page_cache: self.page_cache,
             offset: self.from_offset,
+            includes_empty: false,
         }
     }
 
-    /// Returns an iterator for iterating `DirEntry`s.
-    pub fn iter_entries(&'a mut self) -> impl Iterator<Item = DirEntry> + 'a {
-        let iter = self.iter();
-        iter.filter_map(|entry_item| match self.read_name(&entry_item) {
-            Ok(name_buf) => Some(DirEntry {
-                header: entry_item.header,
-                name: CStr256::from(name_buf),
-            }),
-            Err(_) => None,
+    /// Returns an iterator for iterating `DirEntryItem`s, including the empty ones.
+    pub fn iter_with_empty(&self) -> DirEntryIter<'a> {
+        DirEntryIter {
+            includes_empty: true,
+            ..self.iter()
+        }
+    }
+
+    /// Returns an iterator for iterating `DirEntry`s along with their offsets.
+    ///
+    /// Unlike the other iterators, which stop silently, this iterator yields an error
+    /// if a corrupted entry is met.
+    pub fn iter_entries(&'a mut self) -> impl Iterator<Item = Result<(usize, DirEntry)>> + 'a {
+        let mut iter = self.iter();
+        core::iter::from_fn(move || iter.next_item().transpose()).map(move |entry_item| {
+            let entry_item = entry_item?;
+            let name_buf = self.read_name(&entry_item)?;
+            Ok((
+                entry_item.offset,
+                DirEntry {
+                    header: entry_item.header,
+                    name: CStr256::from(name_buf),
+                },
+            ))
         })
     }
 
     /// Whether the directory contains an entry with the given name.
     pub fn contains_entry(&mut self, name: &str) -> bool {
-        let mut iter = self.iter();
-        iter.any(|entry_item| {
-            if entry_item.name_len() != name.len() {
-                return false;
-            }
-            match self.read_name(&entry_item) {
-                Ok(name_buf) => name_buf == name.as_bytes(),
-                Err(_) => false,
-            }
-        })
+        self.find_entry_item(name).is_some()
     }
 
     /// Returns the target entry with the given name.
     pub fn find_entry_item(&mut self, name: &str) -> Option<DirEntryItem> {
         let mut iter = self.iter();
-        let name_len = name.len();
-        let name_bytes = name.as_bytes();
-        iter.find(|entry_item| {
-            if entry_item.name_len() != name_len {
-                return false;
-            }
+        iter.find(|entry_item| self.has_name(entry_item, name))
+    }
 
-            match self.read_name(entry_item) {
-                Ok(name_buf) => name_buf == name_bytes,
-                Err(_) => false,
-            }
-        })
+    /// Returns the target entry with the given name in the block starting at `block_offset`.
+    pub fn find_entry_item_in_block(
+        &mut self,
+        name: &str,
+        block_offset: usize,
+    ) -> Option<DirEntryItem> {
+        let mut iter = DirEntryIter {
+            offset: block_offset,
+            ..self.iter()
+        }
+        .take_while(|entry_item| entry_item.offset < block_offset + BLOCK_SIZE);
+        iter.find(|entry_item| self.has_name(entry_item, name))
+    }
+
+    /// Whether the entry has the given name.
+    fn has_name(&mut self, entry_item: &DirEntryItem, name: &str) -> bool {
+        if entry_item.name_len() != name.len() {
+            return false;
+        }
+        match self.read_name(entry_item) {
+            Ok(name_buf) => name_buf == name.as_bytes(),
+            Err(_) => false,
+        }
     }
 
     /// Returns the number of entries in the directory.
@@ -245,12 +271,31 @@ impl<'a> DirEntryReader<'a> {
 }
 
 impl DirEntryIter<'_> {
-    /// Reads a `DirEntryItem` at the current offset.
-    fn read_entry_item(&mut self) -> Result<DirEntryItem> {
-        if self.offset >= self.page_cache.pages().size() {
-            return_errno!(Errno::ENOENT);
+    /// Reads the next `DirEntryItem`.
+    ///
+    /// Returns `None` at the end of the directory. If the entry is corrupted, an error is
+    /// returned and the iteration stops.
+    fn next_item(&mut self) -> Result<Option<DirEntryItem>> {
+        loop {
+            if self.offset >= self.page_cache.pages().size() {
+                return Ok(None);
+            }
+
+            let item = match self.read_entry_item() {
+                Ok(item) => item,
+                Err(err) => {
+                    self.offset = self.page_cache.pages().size();
+                    return Err(err);
+                }
+            };
+            if item.ino() != 0 || self.includes_empty {
+                return Ok(Some(item));
+            }
         }
+    }
 
+    /// Reads a `DirEntryItem` at the current offset.
+    fn read_entry_item(&mut self) -> Result<DirEntryItem> {
         let header = self.read_header()?;
         let record_len = header.record_len as usize;
         let item = DirEntryItem {
@@ -268,8 +313,26 @@ impl DirEntryIter<'_> {
             .page_cache
             .pages()
             .read_val::<DirEntryHeader>(self.offset)?;
-        if header.ino == 0 {
-            return_errno!(Errno::ENOENT);
+        let record_len = header.record_len as usize;
+        let actual_len =
+            (DirEntry::HEADER_LEN + header.name_len as usize).align_up(DirEntry::ALIGN);
+        if record_len < actual_len {
+            return_errno_with_message!(Errno::EIO, "the record length is too small");
+        }
+        if record_len % DirEntry::ALIGN != 0 {
+            return_errno_with_message!(Errno::EIO, "the record length is not aligned");
+        }
+        // An entry never spans two blocks.
+        if self.offset % BLOCK_SIZE + record_len > BLOCK_SIZE {
+            return_errno_with_message!(Errno::EIO, "the record length exceeds the block");
+        }
+        if header.ino != 0
+            && matches!(
+                DirEntryFileType::try_from(header.inode_type),
+                Err(_) | Ok(DirEntryFileType::Unknown)
+            )
+        {
+            return_errno_with_message!(Errno::EIO, "the file type is invalid");
         }
         Ok(header)
     }
@@ -279,7 +342,7 @@ impl Iterator for DirEntryIter<'_> {
     type Item = DirEntryItem;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.read_entry_item().ok()
+        self.next_item().ok().flatten()
     }
 }
 
@@ -340,7 +403,12 @@ impl DirEntryItem {
     }
 
     /// Returns the length of the gap between the current entry and the next entry.
+    ///
+    /// The whole record of an empty entry is a gap.
     pub fn gap_len(&self) -> usize {
+        if self.ino() == 0 {
+            return self.record_len();
+        }
         self.record_len() - self.actual_len()
     }
 }
@@ -410,7 +478,7 @@ impl<'a> DirEntryWriter<'a> {
         debug_assert_eq!(header.name_len as usize, name_len);
         let name_bytes = name.as_bytes();
         let mut entry_item_with_enough_gap = None;
-        for entry_item in DirEntryReader::new(self.page_cache, self.offset).iter() {
+        for entry_item in DirEntryReader::new(self.page_cache, self.offset).iter_with_empty() {
             if entry_item_with_enough_gap.is_none()
                 && entry_item.gap_len() >= header.record_len as usize
             {
@@ -421,6 +489,7 @@ impl<'a> DirEntryWriter<'a> {
             }
 
             if check_existence
+                && entry_item.ino() != 0
                 && entry_item.name_len() == name_len
                 && self.read_name(&entry_item)? == name_bytes
             {
@@ -442,6 +511,13 @@ impl<'a> DirEntryWriter<'a> {
         mut header: DirEntryHeader,
         name: &str,
     ) -> Result<()> {
+        // Reuse the whole record of an empty entry.
+        if entry_with_enough_gap.ino() == 0 {
+            header.record_len = entry_with_enough_gap.record_len() as u16;
+            self.offset = entry_with_enough_gap.offset;
+            return self.write_entry(&header, name);
+        }
+
         // Write in the gap between existing entries.
         header.record_len = entry_with_enough_gap.gap_len() as u16;
         entry_with_enough_gap.set_record_len(entry_with_enough_gap.actual_len());
@@ -462,40 +538,50 @@ impl<'a> DirEntryWriter<'a> {
         self.write_entry(&header, name)
     }
 
-    /// Removes and returns an existing `DirEntry` indicated by `name`.
+    /// Removes and returns an existing `DirEntry` indicated by `name` at the current offset.
+    ///
+    /// The record of the entry is merged into the previous entry in the same block. If the
+    /// entry is the first one of the block, it becomes an empty entry instead, unless it is
+    /// the only entry of the last block, in which case the block is removed.
     pub fn remove_entry(&mut self, name: &str) -> Result<DirEntryItem> {
-        let mut pre_entry_item = None;
-        let name_len = name.len();
-        let name_bytes = name.as_bytes();
-        let mut iter = DirEntryReader::new(self.page_cache, DirEntry::PARENT_OFFSET).iter();
-        let Some(target_entry_item) = iter.find(|entry| {
-            if entry.offset < self.offset {
-                pre_entry_item = Some(*entry);
-            }
-
-            entry.offset == self.offset
-                && entry.name_len() == name_len
-                && self.read_name(entry).unwrap() == name_bytes
-        }) else {
+        let target_entry_item = DirEntryReader::new(self.page_cache, self.offset)
+            .iter_with_empty()
+            .next()
+            .filter(|entry_item| entry_item.ino() != 0)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        if target_entry_item.name_len() != name.len()
+            || self.read_name(&target_entry_item)? != name.as_bytes()
+        {
             return_errno!(Errno::ENOENT);
-        };
-        let is_last_entry = iter.next().is_none();
-
-        let mut pre_entry_item = pre_entry_item.unwrap();
-        let pre_offset = pre_entry_item.offset;
-        if is_last_entry {
-            // Shrink the size.
-            let new_size = pre_offset.align_up(BLOCK_SIZE);
-            self.page_cache.resize(new_size)?;
-            pre_entry_item.set_record_len(new_size - pre_offset);
-            self.offset = pre_offset;
-            self.write_header_only(&pre_entry_item.header)?;
-        } else {
+        }
+
+        let target_offset = target_entry_item.offset;
+        let block_offset = target_offset.align_down(BLOCK_SIZE);
+        let pre_entry_item = DirEntryReader::new(self.page_cache, block_offset)
+            .iter_with_empty()
+            .take_while(|entry_item| entry_item.offset < target_offset)
+            .last();
+        let is_last_entry =
+            target_offset + target_entry_item.record_len() == self.page_cache.pages().size();
+
+        let is_block_empty = pre_entry_item
+            .is_none_or(|entry_item| entry_item.ino() == 0 && entry_item.offset == block_offset);
+
+        if is_last_entry && is_block_empty && block_offset > 0 {
+            // The last block becomes empty, so shrink the size.
+            self.page_cache.resize(block_offset)?;
+        } else if let Some(mut pre_entry_item) = pre_entry_item {
             // Update the previous entry.
             pre_entry_item
                 .set_record_len(pre_entry_item.record_len() + target_entry_item.record_len());
-            self.offset = pre_offset;
+            self.offset = pre_entry_item.offset;
             self.write_header_only(&pre_entry_item.header)?;
+        } else {
+            // Leave an empty entry.
+            let mut empty_entry_item = target_entry_item;
+            empty_entry_item.set_ino(0);
+            self.offset = target_offset;
+            self.write_header_only(&empty_entry_item.header)?;
         }
 
         Ok(target_entry_item)
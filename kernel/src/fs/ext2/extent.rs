@@ -0,0 +1,570 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The extent tree of Ext4.
+//!
+//! With the `EXTENTS` feature, an inode can map its blocks with a B+ tree of extents
+//! instead of the indirect block pointers. An extent maps up to 32768 consecutive blocks
+//! of the file to consecutive blocks of the device. The blocks that are not mapped by any
+//! extent are holes.
+//!
+//! The root of the tree is stored in the space of the block pointers in the inode, which
+//! holds up to 4 entries. The other nodes occupy whole blocks, and are cached in the
+//! `IndirectBlockCache` like the indirect blocks.
+
+use ostd::const_assert;
+
+use super::{
+    block_ptr::{BlockPtrs, Ext2Bid},
+    fs::Ext2,
+    indirect_block_cache::{IndirectBlock, IndirectBlockCache},
+    prelude::*,
+};
+
+/// The magic number of the extent nodes.
+const EXTENT_MAGIC: u16 = 0xF30A;
+
+/// The maximum length of an initialized extent.
+///
+/// A length greater than this value indicates an uninitialized extent, whose blocks are
+/// allocated but must be read as zeros.
+const MAX_INIT_LEN: u16 = 32768;
+
+/// The maximum depth of the extent tree.
+const MAX_DEPTH: u16 = 5;
+
+/// The size of the node header, which is also the size of the node entries.
+const ENTRY_SIZE: usize = 12;
+
+/// The maximum number of entries in the root node.
+const ROOT_CAPACITY: u16 = ((core::mem::size_of::<BlockPtrs>() - ENTRY_SIZE) / ENTRY_SIZE) as u16;
+
+/// The maximum number of entries in the other nodes.
+const NODE_CAPACITY: u16 = ((BLOCK_SIZE - ENTRY_SIZE) / ENTRY_SIZE) as u16;
+
+/// The mapping of the file blocks.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Mapping {
+    /// The blocks are mapped to the device blocks starting from `device_bid`.
+    Mapped { device_bid: Ext2Bid, len: Ext2Bid },
+    /// The blocks are not mapped, or are mapped by an uninitialized extent.
+    Hole { len: Ext2Bid },
+}
+
+/// Initializes an empty extent tree whose root is stored in `block_ptrs`.
+pub(super) fn init_root(block_ptrs: &mut BlockPtrs) {
+    *block_ptrs = BlockPtrs::default();
+    let header = RawExtentHeader {
+        magic: EXTENT_MAGIC,
+        entries: 0,
+        max: ROOT_CAPACITY,
+        depth: 0,
+        generation: 0,
+    };
+    block_ptrs.as_bytes_mut()[..ENTRY_SIZE].copy_from_slice(header.as_bytes());
+}
+
+/// The extent tree of an inode.
+pub(super) struct ExtentTree<'a, R> {
+    root: R,
+    nodes: &'a mut IndirectBlockCache,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum NodeLoc {
+    /// The root node in the block pointers of the inode.
+    Root,
+    /// The node in the block.
+    Block(Ext2Bid),
+}
+
+impl<'a, R: Deref<Target = BlockPtrs>> ExtentTree<'a, R> {
+    /// Creates the extent tree with the `root` node in the block pointers.
+    pub fn new(root: R, nodes: &'a mut IndirectBlockCache) -> Self {
+        Self { root, nodes }
+    }
+
+    /// Looks up the mapping of the file block `bid`.
+    ///
+    /// The length of the returned mapping is the number of the consecutive blocks starting
+    /// from `bid` that are mapped in the same way.
+    pub fn lookup(&mut self, bid: Ext2Bid) -> Result<Mapping> {
+        let mut loc = NodeLoc::Root;
+        let mut header = self.read_header(loc, None)?;
+        // The end of the file blocks covered by the current node.
+        let mut end = Ext2Bid::MAX;
+
+        while header.depth > 0 {
+            if header.entries == 0 {
+                return_errno_with_message!(Errno::EIO, "the extent index node is empty");
+            }
+            let idx = self.search(loc, &header, bid)?.unwrap_or(0);
+            if idx + 1 < header.entries as usize {
+                let next = self.read_entry::<RawExtentIdx>(loc, idx + 1)?;
+                end = end.min(next.block);
+            }
+            let child = self.read_entry::<RawExtentIdx>(loc, idx)?.leaf()?;
+
+            loc = NodeLoc::Block(child);
+            header = self.read_header(loc, Some(header.depth - 1))?;
+        }
+
+        let idx = self.search(loc, &header, bid)?;
+        let next_idx = idx.map_or(0, |idx| idx + 1);
+        if next_idx < header.entries as usize {
+            let next = self.read_entry::<RawExtent>(loc, next_idx)?;
+            end = end.min(next.block);
+        }
+
+        if let Some(idx) = idx {
+            let extent = self.read_entry::<RawExtent>(loc, idx)?;
+            let extent_end = extent.end()?;
+            if bid < extent_end {
+                let len = extent_end - bid;
+                if extent.is_uninit() {
+                    return Ok(Mapping::Hole { len });
+                }
+                return Ok(Mapping::Mapped {
+                    device_bid: extent.start()? + (bid - extent.block),
+                    len,
+                });
+            }
+        }
+
+        if end <= bid {
+            return_errno_with_message!(Errno::EIO, "the extents are not sorted");
+        }
+        Ok(Mapping::Hole { len: end - bid })
+    }
+
+    /// Finds the index of the last entry whose first file block is not after `bid`.
+    fn search(
+        &mut self,
+        loc: NodeLoc,
+        header: &RawExtentHeader,
+        bid: Ext2Bid,
+    ) -> Result<Option<usize>> {
+        // Both the extents and the indexes start with the first file block.
+        let mut left = 0;
+        let mut right = header.entries as usize;
+        while left < right {
+            let mid = (left + right) / 2;
+            let block: u32 = self.read_val(loc, entry_offset(mid))?;
+            if block <= bid {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        Ok(left.checked_sub(1))
+    }
+
+    /// Returns the path from the root to the rightmost leaf.
+    fn rightmost_path(&mut self) -> Result<Vec<(NodeLoc, RawExtentHeader)>> {
+        let mut loc = NodeLoc::Root;
+        let mut header = self.read_header(loc, None)?;
+        let mut path = Vec::with_capacity(header.depth as usize + 1);
+
+        while header.depth > 0 {
+            path.push((loc, header));
+            if header.entries == 0 {
+                return_errno_with_message!(Errno::EIO, "the extent index node is empty");
+            }
+            let idx = self.read_entry::<RawExtentIdx>(loc, header.entries as usize - 1)?;
+
+            loc = NodeLoc::Block(idx.leaf()?);
+            header = self.read_header(loc, Some(header.depth - 1))?;
+        }
+        path.push((loc, header));
+
+        Ok(path)
+    }
+
+    /// Reads and checks the header of a node.
+    fn read_header(&mut self, loc: NodeLoc, depth: Option<u16>) -> Result<RawExtentHeader> {
+        let header: RawExtentHeader = self.read_val(loc, 0)?;
+        let capacity = match loc {
+            NodeLoc::Root => ROOT_CAPACITY,
+            NodeLoc::Block(_) => NODE_CAPACITY,
+        };
+        if header.magic != EXTENT_MAGIC
+            || header.entries > header.max
+            || header.max > capacity
+            || header.depth > MAX_DEPTH
+            || depth.is_some_and(|depth| depth != header.depth)
+        {
+            return_errno_with_message!(Errno::EIO, "invalid extent node header");
+        }
+        Ok(header)
+    }
+
+    fn read_entry<T: Pod>(&mut self, loc: NodeLoc, idx: usize) -> Result<T> {
+        self.read_val(loc, entry_offset(idx))
+    }
+
+    fn read_val<T: Pod>(&mut self, loc: NodeLoc, offset: usize) -> Result<T> {
+        match loc {
+            NodeLoc::Root => {
+                let bytes = &self.root.as_bytes()[offset..offset + core::mem::size_of::<T>()];
+                Ok(T::from_bytes(bytes))
+            }
+            NodeLoc::Block(bid) => self.nodes.find(bid)?.read_val(offset),
+        }
+    }
+}
+
+impl<R: DerefMut<Target = BlockPtrs>> ExtentTree<'_, R> {
+    /// Maps the file blocks starting from `bid` to the blocks in `device_range`.
+    ///
+    /// The file blocks must be after all the mapped blocks. The new nodes are allocated from
+    /// the `block_group_idx` group first.
+    ///
+    /// Returns the number of the mapped blocks, which may be less than the length of
+    /// `device_range` since the length of an extent is limited.
+    pub fn append(
+        &mut self,
+        bid: Ext2Bid,
+        device_range: Range<Ext2Bid>,
+        fs: &Ext2,
+        block_group_idx: usize,
+    ) -> Result<Ext2Bid> {
+        let max_len = (device_range.len() as Ext2Bid).min(MAX_INIT_LEN as Ext2Bid);
+
+        loop {
+            let path = self.rightmost_path()?;
+            let (leaf, mut leaf_header) = *path.last().unwrap();
+
+            // Extends the last extent if possible.
+            if leaf_header.entries > 0 {
+                let last_idx = leaf_header.entries as usize - 1;
+                let mut last = self.read_entry::<RawExtent>(leaf, last_idx)?;
+                let last_end = last.end()?;
+                if last_end > bid {
+                    return_errno_with_message!(Errno::EIO, "the blocks have been mapped");
+                }
+                if !last.is_uninit()
+                    && last_end == bid
+                    && last.start()? + last.len() == device_range.start
+                    && last.len < MAX_INIT_LEN
+                {
+                    let len = max_len.min((MAX_INIT_LEN - last.len) as Ext2Bid);
+                    last.len += len as u16;
+                    self.write_entry(leaf, last_idx, &last)?;
+                    return Ok(len);
+                }
+            }
+
+            // Inserts a new extent if there is room in the leaf.
+            if leaf_header.entries < leaf_header.max {
+                let extent = RawExtent {
+                    block: bid,
+                    len: max_len as u16,
+                    start_hi: 0,
+                    start_lo: device_range.start,
+                };
+                self.write_entry(leaf, leaf_header.entries as usize, &extent)?;
+                leaf_header.entries += 1;
+                self.write_val(leaf, 0, &leaf_header)?;
+                return Ok(max_len);
+            }
+
+            // Adds a new leaf under the deepest index node that has room, or adds a new level
+            // if all the index nodes are full. Then retries.
+            match path
+                .iter()
+                .rposition(|(_, header)| header.depth > 0 && header.entries < header.max)
+            {
+                Some(level) => {
+                    let (loc, header) = path[level];
+                    self.add_branch(loc, header, bid, fs, block_group_idx)?;
+                }
+                None => self.grow_depth(fs, block_group_idx)?,
+            }
+        }
+    }
+
+    /// Adds a branch of new nodes to the index node at `loc`, down to an empty leaf.
+    ///
+    /// The new branch covers the file blocks starting from `bid`.
+    fn add_branch(
+        &mut self,
+        loc: NodeLoc,
+        mut header: RawExtentHeader,
+        bid: Ext2Bid,
+        fs: &Ext2,
+        block_group_idx: usize,
+    ) -> Result<()> {
+        // Allocates the nodes from the leaf upwards.
+        let mut branch: Vec<Ext2Bid> = Vec::with_capacity(header.depth as usize);
+        for depth in 0..header.depth {
+            match self.alloc_node(fs, block_group_idx, depth) {
+                Ok(node_bid) => branch.push(node_bid),
+                Err(err) => {
+                    for node_bid in branch {
+                        self.free_node(node_bid, fs)?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        for pair in branch.windows(2) {
+            let (child_bid, node) = (pair[0], NodeLoc::Block(pair[1]));
+            self.write_entry(node, 0, &RawExtentIdx::new(bid, child_bid))?;
+            let mut node_header: RawExtentHeader = self.read_val(node, 0)?;
+            node_header.entries = 1;
+            self.write_val(node, 0, &node_header)?;
+        }
+
+        let idx = RawExtentIdx::new(bid, *branch.last().unwrap());
+        self.write_entry(loc, header.entries as usize, &idx)?;
+        header.entries += 1;
+        self.write_val(loc, 0, &header)
+    }
+
+    /// Moves the entries of the root to a new node, and makes the node the only child of
+    /// the root.
+    fn grow_depth(&mut self, fs: &Ext2, block_group_idx: usize) -> Result<()> {
+        let mut root_header = self.read_header(NodeLoc::Root, None)?;
+        if root_header.depth >= MAX_DEPTH {
+            return_errno_with_message!(Errno::EFBIG, "the extent tree is too deep");
+        }
+
+        let node_bid = self.alloc_node(fs, block_group_idx, root_header.depth)?;
+        let node = NodeLoc::Block(node_bid);
+        let mut first_block = 0;
+        for idx in 0..root_header.entries as usize {
+            // The entries of both the leaf and index nodes are copied as is. They start with
+            // the first file block.
+            let entry: RawExtentIdx = self.read_entry(NodeLoc::Root, idx)?;
+            if idx == 0 {
+                first_block = entry.block;
+            }
+            self.write_entry(node, idx, &entry)?;
+        }
+        let node_header = RawExtentHeader {
+            max: NODE_CAPACITY,
+            ..root_header
+        };
+        self.write_val(node, 0, &node_header)?;
+
+        let idx = RawExtentIdx::new(first_block, node_bid);
+        self.write_entry(NodeLoc::Root, 0, &idx)?;
+        root_header.entries = 1;
+        root_header.depth += 1;
+        self.write_val(NodeLoc::Root, 0, &root_header)
+    }
+
+    /// Unmaps the file blocks starting from `bid`.
+    ///
+    /// The unmapped blocks and the nodes that become empty are freed.
+    pub fn truncate(&mut self, bid: Ext2Bid, fs: &Ext2) -> Result<()> {
+        let header = self.read_header(NodeLoc::Root, None)?;
+        let is_empty = self.truncate_node(NodeLoc::Root, header, bid, fs)?;
+        if is_empty && header.depth > 0 {
+            init_root(&mut self.root);
+        }
+        Ok(())
+    }
+
+    /// Unmaps the file blocks starting from `bid` in the subtree of the node at `loc`.
+    ///
+    /// Returns whether the node becomes empty.
+    fn truncate_node(
+        &mut self,
+        loc: NodeLoc,
+        mut header: RawExtentHeader,
+        bid: Ext2Bid,
+        fs: &Ext2,
+    ) -> Result<bool> {
+        let mut count = header.entries as usize;
+
+        if header.depth == 0 {
+            while count > 0 {
+                let mut extent = self.read_entry::<RawExtent>(loc, count - 1)?;
+                let start = extent.start()?;
+                let len = extent.len();
+
+                if extent.block >= bid {
+                    fs.free_blocks(start..start + len)?;
+                    count -= 1;
+                    continue;
+                }
+                if extent.end()? > bid {
+                    let new_len = bid - extent.block;
+                    fs.free_blocks(start + new_len..start + len)?;
+                    extent.set_len(new_len);
+                    self.write_entry(loc, count - 1, &extent)?;
+                }
+                break;
+            }
+        } else {
+            while count > 0 {
+                let idx = self.read_entry::<RawExtentIdx>(loc, count - 1)?;
+                let child = idx.leaf()?;
+                let child_header =
+                    self.read_header(NodeLoc::Block(child), Some(header.depth - 1))?;
+
+                let is_child_empty =
+                    self.truncate_node(NodeLoc::Block(child), child_header, bid, fs)?;
+                if is_child_empty {
+                    self.free_node(child, fs)?;
+                    count -= 1;
+                }
+                // The nodes before this one only cover the blocks before `bid`.
+                if !is_child_empty || idx.block < bid {
+                    break;
+                }
+            }
+        }
+
+        if count != header.entries as usize {
+            header.entries = count as u16;
+            self.write_val(loc, 0, &header)?;
+        }
+        Ok(count == 0)
+    }
+
+    /// Allocates an empty node at `depth`.
+    fn alloc_node(&mut self, fs: &Ext2, block_group_idx: usize, depth: u16) -> Result<Ext2Bid> {
+        let node_bid = fs
+            .alloc_blocks(block_group_idx, 1)
+            .ok_or_else(|| Error::new(Errno::ENOSPC))?
+            .start;
+        self.nodes.insert(node_bid, IndirectBlock::alloc()?)?;
+
+        let header = RawExtentHeader {
+            magic: EXTENT_MAGIC,
+            entries: 0,
+            max: NODE_CAPACITY,
+            depth,
+            generation: 0,
+        };
+        self.write_val(NodeLoc::Block(node_bid), 0, &header)?;
+        Ok(node_bid)
+    }
+
+    fn free_node(&mut self, node_bid: Ext2Bid, fs: &Ext2) -> Result<()> {
+        self.nodes.remove(node_bid);
+        fs.free_blocks(node_bid..node_bid + 1)
+    }
+
+    fn write_entry<T: Pod>(&mut self, loc: NodeLoc, idx: usize, val: &T) -> Result<()> {
+        self.write_val(loc, entry_offset(idx), val)
+    }
+
+    fn write_val<T: Pod>(&mut self, loc: NodeLoc, offset: usize, val: &T) -> Result<()> {
+        match loc {
+            NodeLoc::Root => {
+                self.root.as_bytes_mut()[offset..offset + core::mem::size_of::<T>()]
+                    .copy_from_slice(val.as_bytes());
+                Ok(())
+            }
+            NodeLoc::Block(bid) => self.nodes.find_mut(bid)?.write_val(offset, val),
+        }
+    }
+}
+
+fn entry_offset(idx: usize) -> usize {
+    ENTRY_SIZE * (idx + 1)
+}
+
+const_assert!(core::mem::size_of::<RawExtentHeader>() == ENTRY_SIZE);
+const_assert!(core::mem::size_of::<RawExtent>() == ENTRY_SIZE);
+const_assert!(core::mem::size_of::<RawExtentIdx>() == ENTRY_SIZE);
+
+/// The header of the extent nodes.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawExtentHeader {
+    /// Magic number, 0xF30A.
+    magic: u16,
+    /// Number of valid entries following the header.
+    entries: u16,
+    /// Maximum number of entries that could follow the header.
+    max: u16,
+    /// Depth of this node in the extent tree, 0 for the leaf nodes.
+    depth: u16,
+    /// Generation of the tree (not used).
+    generation: u32,
+}
+
+/// The entry of the leaf nodes.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawExtent {
+    /// First file block number that this extent covers.
+    block: u32,
+    /// Number of blocks covered by the extent.
+    len: u16,
+    /// Upper 16 bits of the block number to which this extent points.
+    start_hi: u16,
+    /// Lower 32 bits of the block number to which this extent points.
+    start_lo: u32,
+}
+
+impl RawExtent {
+    fn is_uninit(&self) -> bool {
+        self.len > MAX_INIT_LEN
+    }
+
+    fn len(&self) -> Ext2Bid {
+        if self.is_uninit() {
+            (self.len - MAX_INIT_LEN) as Ext2Bid
+        } else {
+            self.len as Ext2Bid
+        }
+    }
+
+    fn set_len(&mut self, len: Ext2Bid) {
+        self.len = if self.is_uninit() {
+            len as u16 + MAX_INIT_LEN
+        } else {
+            len as u16
+        };
+    }
+
+    /// Returns the first file block after the extent.
+    fn end(&self) -> Result<Ext2Bid> {
+        self.block
+            .checked_add(self.len())
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the extent is beyond the file"))
+    }
+
+    fn start(&self) -> Result<Ext2Bid> {
+        if self.start_hi != 0 || self.start_lo.checked_add(self.len()).is_none() {
+            return_errno_with_message!(Errno::EIO, "the extent is beyond 32-bit block IDs");
+        }
+        Ok(self.start_lo)
+    }
+}
+
+/// The entry of the index nodes.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawExtentIdx {
+    /// First file block number that this index node covers.
+    block: u32,
+    /// Lower 32 bits of the block number of the child node.
+    leaf_lo: u32,
+    /// Upper 16 bits of the block number of the child node.
+    leaf_hi: u16,
+    unused: u16,
+}
+
+impl RawExtentIdx {
+    fn new(block: u32, leaf: Ext2Bid) -> Self {
+        Self {
+            block,
+            leaf_lo: leaf,
+            leaf_hi: 0,
+            unused: 0,
+        }
+    }
+
+    fn leaf(&self) -> Result<Ext2Bid> {
+        if self.leaf_hi != 0 {
+            return_errno_with_message!(Errno::EIO, "the extent node is beyond 32-bit block IDs");
+        }
+        Ok(self.leaf_lo)
+    }
+}
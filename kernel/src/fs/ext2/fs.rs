@@ -2,13 +2,17 @@
 
 #![expect(dead_code)]
 
+use spin::Once;
+
 use super::{
-    block_group::{BlockGroup, RawGroupDescriptor},
+    block_group::{group_descriptor_checksum, BlockGroup, RawGroupDescriptor},
     block_ptr::Ext2Bid,
     inode::{FilePerm, Inode, InodeDesc, RawInode},
+    journal::Journal,
     prelude::*,
-    super_block::{RawSuperBlock, SuperBlock, SUPER_BLOCK_OFFSET},
+    super_block::{FeatureInCompatSet, RawSuperBlock, SuperBlock, SUPER_BLOCK_OFFSET},
 };
+use crate::fs::quota::Quota;
 
 /// The root inode number.
 const ROOT_INO: u32 = 2;
@@ -23,13 +27,58 @@ pub struct Ext2 {
     blocks_per_group: Ext2Bid,
     inode_size: usize,
     block_size: usize,
+    desc_size: usize,
+    has_group_descriptor_checksum: bool,
+    uuid: [u8; 16],
+    hash_seed: [u32; 4],
+    is_hash_unsigned: bool,
     group_descriptors_segment: USegment,
+    journal: Once<Journal>,
+    quota: Quota,
     self_ref: Weak<Self>,
 }
 
 impl Ext2 {
     /// Opens and loads an Ext2 from the `block_device`.
+    ///
+    /// If the filesystem has a journal, the committed transactions in the journal are
+    /// replayed first, and all the later updates of the metadata go through the journal.
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let ext2 = Self::load(block_device.clone())?;
+        let Some(journal_ino) = ext2.super_block().journal_ino() else {
+            return Ok(ext2);
+        };
+
+        let journal = {
+            let device_ranges = ext2.lookup_inode(journal_ino)?.device_ranges()?;
+            Journal::load(block_device.as_ref(), &device_ranges)?
+        };
+        let ext2 = if journal.is_dirty() {
+            let replays = ext2
+                .super_block()
+                .feature_incompat()
+                .contains(FeatureInCompatSet::RECOVER);
+            journal.recover(block_device.as_ref(), replays)?;
+            // The metadata may be overwritten by the replay, so it must be loaded again.
+            drop(ext2);
+            Self::load(block_device)?
+        } else {
+            ext2
+        };
+
+        if ext2
+            .super_block()
+            .feature_incompat()
+            .contains(FeatureInCompatSet::RECOVER)
+        {
+            ext2.super_block.write().set_needs_recovery(false);
+        }
+        ext2.journal.call_once(|| journal);
+        Ok(ext2)
+    }
+
+    /// Loads an Ext2 from the `block_device` without the journal.
+    fn load(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
         // Load the superblock
         // TODO: if the main superblock is corrupted, should we load the backup?
         let super_block = {
@@ -43,9 +92,7 @@ impl Ext2 {
         );
 
         let group_descriptors_segment: USegment = {
-            let npages = ((super_block.block_groups_count() as usize)
-                * core::mem::size_of::<RawGroupDescriptor>())
-            .div_ceil(BLOCK_SIZE);
+            let npages = super_block.group_descriptors_blocks();
             let segment = FrameAllocOptions::new()
                 .zeroed(false)
                 .alloc_segment(npages)?;
@@ -85,6 +132,11 @@ impl Ext2 {
             blocks_per_group: super_block.blocks_per_group(),
             inode_size: super_block.inode_size(),
             block_size: super_block.block_size(),
+            desc_size: super_block.desc_size(),
+            has_group_descriptor_checksum: super_block.has_group_descriptor_checksum(),
+            uuid: super_block.uuid(),
+            hash_seed: super_block.hash_seed(),
+            is_hash_unsigned: super_block.is_hash_unsigned(),
             block_groups: load_block_groups(
                 weak_ref.clone(),
                 block_device.as_ref(),
@@ -94,6 +146,8 @@ impl Ext2 {
             block_device,
             super_block: RwMutex::new(Dirty::new(super_block)),
             group_descriptors_segment,
+            journal: Once::new(),
+            quota: Quota::new(),
             self_ref: weak_ref.clone(),
         });
         Ok(ext2)
@@ -114,6 +168,16 @@ impl Ext2 {
         self.inode_size
     }
 
+    /// Returns the seed of the directory hash.
+    pub(super) fn hash_seed(&self) -> [u32; 4] {
+        self.hash_seed
+    }
+
+    /// Returns whether the directory hash treats the characters as unsigned.
+    pub(super) fn is_hash_unsigned(&self) -> bool {
+        self.is_hash_unsigned
+    }
+
     /// Returns the number of inodes in each block group.
     pub fn inodes_per_group(&self) -> u32 {
         self.inodes_per_group
@@ -129,6 +193,11 @@ impl Ext2 {
         self.super_block.read()
     }
 
+    /// Returns the disk quotas.
+    pub fn quota(&self) -> &Quota {
+        &self.quota
+    }
+
     /// Returns the root inode.
     pub fn root_inode(&self) -> Result<Arc<Inode>> {
         self.lookup_inode(ROOT_INO)
@@ -147,18 +216,46 @@ impl Ext2 {
         dir_block_group_idx: usize,
         inode_type: InodeType,
         file_perm: FilePerm,
+        project_id: u32,
     ) -> Result<Arc<Inode>> {
         let (block_group_idx, ino) =
             self.alloc_ino(dir_block_group_idx, inode_type == InodeType::Dir)?;
+        let block_group = &self.block_groups[block_group_idx];
+        block_group.init_raw_inode(self.inode_idx(ino), self.extra_isize());
+        block_group.write_project_id(self.inode_idx(ino), project_id);
+
         let inode = {
-            let inode_desc = InodeDesc::new(inode_type, file_perm);
+            let mut inode_desc = InodeDesc::new(inode_type, file_perm);
+            inode_desc.set_project_id(block_group.read_project_id(self.inode_idx(ino)));
+            if matches!(inode_type, InodeType::File | InodeType::Dir)
+                && self
+                    .super_block()
+                    .feature_incompat()
+                    .contains(FeatureInCompatSet::EXTENTS)
+            {
+                inode_desc.enable_extents();
+            }
             Inode::new(ino, block_group_idx, inode_desc, self.self_ref.clone())
         };
-        let block_group = &self.block_groups[block_group_idx];
         block_group.insert_cache(self.inode_idx(ino), inode.clone());
         Ok(inode)
     }
 
+    /// Returns the size of the extra fields of new inodes.
+    ///
+    /// The extra fields are located after the 128-byte raw inode, and are followed by
+    /// the extended attributes stored in the inode.
+    fn extra_isize(&self) -> u16 {
+        const DEFAULT_EXTRA_ISIZE: usize = 32;
+
+        let max_extra_isize = self.inode_size - core::mem::size_of::<RawInode>();
+        let want_extra_isize = match self.super_block().want_extra_isize() {
+            0 => DEFAULT_EXTRA_ISIZE,
+            want_extra_isize => want_extra_isize,
+        };
+        want_extra_isize.min(max_extra_isize) as u16
+    }
+
     /// Allocates a new inode number, internally used by `new_inode`.
     ///
     /// Attempts to allocate from the `dir_block_group_idx` group first.
@@ -204,15 +301,40 @@ impl Ext2 {
         Ok(())
     }
 
+    /// Reads the bytes at `offset` after the 128-byte raw inode.
+    pub(super) fn read_inode_extra(&self, ino: u32, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let (_, block_group) = self.block_group_of_ino(ino)?;
+        block_group.read_raw_inode_extra(self.inode_idx(ino), offset, buf);
+        Ok(())
+    }
+
+    /// Writes the bytes at `offset` after the 128-byte raw inode.
+    pub(super) fn write_inode_extra(&self, ino: u32, offset: usize, buf: &[u8]) -> Result<()> {
+        let (_, block_group) = self.block_group_of_ino(ino)?;
+        block_group.write_raw_inode_extra(self.inode_idx(ino), offset, buf);
+        Ok(())
+    }
+
     /// Writes back the block group descriptor to the descriptors table.
     pub(super) fn sync_group_descriptor(
         &self,
         block_group_idx: usize,
         raw_descriptor: &RawGroupDescriptor,
     ) -> Result<()> {
-        let offset = block_group_idx * core::mem::size_of::<RawGroupDescriptor>();
+        let offset = block_group_idx * self.desc_size;
         self.group_descriptors_segment
             .write_val(offset, raw_descriptor)?;
+
+        if self.has_group_descriptor_checksum {
+            let mut buf = vec![0u8; self.desc_size];
+            self.group_descriptors_segment
+                .read_bytes(offset, &mut buf)?;
+            let checksum = group_descriptor_checksum(&self.uuid, block_group_idx, &buf);
+            self.group_descriptors_segment.write_val(
+                offset + core::mem::offset_of!(RawGroupDescriptor, checksum),
+                &checksum,
+            )?;
+        }
         Ok(())
     }
 
@@ -295,6 +417,10 @@ impl Ext2 {
             current_range.start += range_in_group.len() as Ext2Bid
         }
 
+        if let Some(journal) = self.journal.get() {
+            journal.forget_blocks(range);
+        }
+
         Ok(())
     }
 
@@ -302,7 +428,13 @@ impl Ext2 {
     pub(super) fn read_blocks(&self, bid: Ext2Bid, bio_segment: BioSegment) -> Result<()> {
         let status = self
             .block_device
-            .read_blocks(Bid::new(bid as u64), bio_segment)?;
+            .read_blocks(Bid::new(bid as u64), bio_segment.clone())?;
+        if let BioStatus::Complete = status {
+            // The metadata blocks in the running transaction are newer.
+            if let Some(journal) = self.journal.get() {
+                journal.copy_blocks_to(bid, &bio_segment)?;
+            }
+        }
         match status {
             BioStatus::Complete => Ok(()),
             err_status => Err(Error::from(err_status)),
@@ -315,6 +447,16 @@ impl Ext2 {
         bid: Ext2Bid,
         bio_segment: BioSegment,
     ) -> Result<BioWaiter> {
+        // The metadata blocks in the running transaction are newer, so they are read
+        // synchronously to be patched after the read completes.
+        if let Some(journal) = self.journal.get() {
+            let range = bid..bid + bio_segment.nblocks() as Ext2Bid;
+            if journal.contains_blocks(range) {
+                self.read_blocks(bid, bio_segment)?;
+                return Ok(BioWaiter::new());
+            }
+        }
+
         let waiter = self
             .block_device
             .read_blocks_async(Bid::new(bid as u64), bio_segment)?;
@@ -344,38 +486,92 @@ impl Ext2 {
         Ok(waiter)
     }
 
+    /// Writes contiguous metadata blocks starting from the `bid` asynchronously.
+    ///
+    /// With a journal, the blocks are held in the running transaction, which is committed
+    /// when the metadata is synced.
+    pub(super) fn write_metadata_async(
+        &self,
+        bid: Ext2Bid,
+        bio_segment: BioSegment,
+    ) -> Result<BioWaiter> {
+        if let Some(journal) = self.journal.get() {
+            journal.add_blocks(bid, &bio_segment)?;
+            return Ok(BioWaiter::new());
+        }
+        self.write_blocks_async(bid, bio_segment)
+    }
+
+    /// Writes the metadata bytes at the `offset` of the block device asynchronously.
+    ///
+    /// With a journal, the bytes are held in the running transaction, which is committed
+    /// when the metadata is synced.
+    pub(super) fn write_metadata_bytes_async(
+        &self,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<BioWaiter> {
+        if let Some(journal) = self.journal.get() {
+            journal.add_bytes(self.block_device(), offset, buf)?;
+            return Ok(BioWaiter::new());
+        }
+        let waiter = self.block_device.write_bytes_async(offset, buf)?;
+        Ok(waiter)
+    }
+
     /// Writes back the metadata to the block device.
     pub fn sync_metadata(&self) -> Result<()> {
-        // If the superblock is clean, the block groups must be clean.
-        if !self.super_block.read().is_dirty() {
-            return Ok(());
+        let mut super_block = self.super_block.write();
+
+        if super_block.is_dirty() {
+            // Writes back the metadata of block groups
+            for block_group in &self.block_groups {
+                block_group.sync_metadata()?;
+            }
+
+            // Writes back the main group descriptor table.
+            let group_descriptors_bio_segment = BioSegment::new_from_segment(
+                self.group_descriptors_segment.clone(),
+                BioDirection::ToDevice,
+            );
+            self.write_metadata_async(
+                super_block.group_descriptors_bid(0).to_raw() as Ext2Bid,
+                group_descriptors_bio_segment,
+            )?
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to sync main metadata"))?;
         }
 
-        let mut super_block = self.super_block.write();
-        // Writes back the metadata of block groups
-        for block_group in &self.block_groups {
-            block_group.sync_metadata()?;
+        // Commits the metadata held in the journal, including the inodes and the directory
+        // blocks written back before.
+        if let Some(journal) = self.journal.get() {
+            journal.commit(self.block_device(), |needs_recovery| {
+                let mut super_block = **super_block;
+                super_block.set_needs_recovery(needs_recovery);
+                self.block_device.write_bytes(
+                    SUPER_BLOCK_OFFSET,
+                    RawSuperBlock::from(&super_block).as_bytes(),
+                )?;
+                Ok(())
+            })?;
         }
 
-        // Writes back the main superblock and group descriptor table.
-        let mut bio_waiter = BioWaiter::new();
+        // If the superblock is clean, the block groups must be clean.
+        if !super_block.is_dirty() {
+            return Ok(());
+        }
+
+        // Writes back the main superblock.
         let raw_super_block = RawSuperBlock::from((*super_block).deref());
-        bio_waiter.concat(
-            self.block_device
-                .write_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?,
-        );
+        self.block_device
+            .write_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to sync main metadata"))?;
+
         let group_descriptors_bio_segment = BioSegment::new_from_segment(
             self.group_descriptors_segment.clone(),
             BioDirection::ToDevice,
         );
-        bio_waiter.concat(self.block_device.write_blocks_async(
-            super_block.group_descriptors_bid(0),
-            group_descriptors_bio_segment.clone(),
-        )?);
-        bio_waiter
-            .wait()
-            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to sync main metadata"))?;
-        drop(bio_waiter);
 
         // Writes back the backups of superblock and group descriptor table.
         let mut raw_super_block_backup = raw_super_block;
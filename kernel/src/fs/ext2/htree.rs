@@ -0,0 +1,395 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The hash tree index of directories.
+//!
+//! With the `INDEX_DIR` flag, the entries of a directory are indexed by the hashes of their
+//! names. The first block of the directory is the root of a B-tree, whose leaves are the
+//! ordinary directory blocks. The index nodes are disguised as directory blocks that only
+//! contain empty entries, so they are transparent to the linear search.
+//!
+//! The index is only used for lookups. Since the index is not maintained on modification,
+//! the flag is cleared once the directory is modified.
+
+use super::{
+    dir::{DirEntryItem, DirEntryReader},
+    prelude::*,
+};
+
+/// The offset of the root information in the root block, which is after the "." and ".."
+/// entries.
+const ROOT_INFO_OFFSET: usize = 24;
+
+/// The offset of the index entries in the root block.
+const ROOT_ENTRIES_OFFSET: usize = ROOT_INFO_OFFSET + core::mem::size_of::<RawRootInfo>();
+
+/// The offset of the index entries in the other index nodes, which is after an empty entry.
+const NODE_ENTRIES_OFFSET: usize = 8;
+
+/// The maximum number of the index entries in the root block.
+const ROOT_LIMIT: usize = (BLOCK_SIZE - ROOT_ENTRIES_OFFSET) / INDEX_ENTRY_SIZE;
+
+/// The maximum number of the index entries in the other index nodes.
+const NODE_LIMIT: usize = (BLOCK_SIZE - NODE_ENTRIES_OFFSET) / INDEX_ENTRY_SIZE;
+
+/// The size of an index entry.
+const INDEX_ENTRY_SIZE: usize = core::mem::size_of::<RawIndexEntry>();
+
+/// The maximum levels of the index nodes below the root, with the `LARGEDIR` feature.
+const MAX_INDIRECT_LEVELS: u8 = 2;
+
+/// The mask of the block number in the index entries.
+///
+/// The high bits are reserved for future use.
+const BLOCK_MASK: u32 = 0x0FFF_FFFF;
+
+/// The default seed of the hash functions if the superblock does not provide one.
+const DEFAULT_SEED: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+/// Looks up the entry with the given name in the directory by its hash index.
+///
+/// An error is returned if the index is corrupted or unsupported, in which case the caller
+/// should fall back to the linear search.
+pub(super) fn lookup(
+    page_cache: &PageCache,
+    name: &str,
+    hash_seed: [u32; 4],
+    is_hash_unsigned: bool,
+) -> Result<Option<DirEntryItem>> {
+    let root_info: RawRootInfo = page_cache.pages().read_val(ROOT_INFO_OFFSET)?;
+    if root_info.reserved_zero != 0
+        || root_info.info_length as usize != core::mem::size_of::<RawRootInfo>()
+        || root_info.indirect_levels > MAX_INDIRECT_LEVELS
+    {
+        return_errno_with_message!(Errno::EIO, "the index root is corrupted");
+    }
+
+    let mut hash_version = HashVersion::try_from(root_info.hash_version)
+        .map_err(|_| Error::with_message(Errno::EOPNOTSUPP, "the hash version is unsupported"))?;
+    if is_hash_unsigned {
+        hash_version = hash_version.to_unsigned();
+    }
+    let hash = dx_hash(name.as_bytes(), hash_version, hash_seed)?;
+
+    // Descends from the root to the leaf that may contain the entry.
+    let mut frames = Vec::with_capacity(root_info.indirect_levels as usize + 1);
+    let mut entries_offset = ROOT_ENTRIES_OFFSET;
+    let mut limit = ROOT_LIMIT;
+    let mut leaf_offset = loop {
+        let mut frame = IndexFrame::load(page_cache, entries_offset, limit)?;
+        frame.search(page_cache, hash)?;
+        let block_offset = frame.block_offset(page_cache)?;
+        frames.push(frame);
+        if frames.len() > root_info.indirect_levels as usize {
+            break block_offset;
+        }
+        entries_offset = block_offset + NODE_ENTRIES_OFFSET;
+        limit = NODE_LIMIT;
+    };
+
+    let mut reader = DirEntryReader::new(page_cache, 0);
+    loop {
+        if let Some(entry_item) = reader.find_entry_item_in_block(name, leaf_offset) {
+            return Ok(Some(entry_item));
+        }
+        match next_leaf(page_cache, &mut frames, hash)? {
+            Some(next_leaf_offset) => leaf_offset = next_leaf_offset,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Moves to the next leaf if it may contain the entries with the given hash.
+///
+/// The entries with the same hash may span multiple leaves, in which case the hashes in the
+/// index entries of the subsequent leaves are the same as the hash of the first leaf.
+fn next_leaf(
+    page_cache: &PageCache,
+    frames: &mut [IndexFrame],
+    hash: u32,
+) -> Result<Option<usize>> {
+    let Some(level) = frames.iter().rposition(|frame| frame.at + 1 < frame.count) else {
+        return Ok(None);
+    };
+
+    frames[level].at += 1;
+    if frames[level].hash(page_cache)? & !1 != hash {
+        return Ok(None);
+    }
+
+    // Descends to the leftmost leaf of the subtree.
+    for level in level + 1..frames.len() {
+        let block_offset = frames[level - 1].block_offset(page_cache)?;
+        frames[level] =
+            IndexFrame::load(page_cache, block_offset + NODE_ENTRIES_OFFSET, NODE_LIMIT)?;
+    }
+    frames.last().unwrap().block_offset(page_cache).map(Some)
+}
+
+/// The position in an index node.
+#[derive(Clone, Copy, Debug)]
+struct IndexFrame {
+    /// The offset of the index entries in the directory.
+    entries_offset: usize,
+    /// The number of the index entries.
+    count: usize,
+    /// The index of the current entry.
+    at: usize,
+}
+
+impl IndexFrame {
+    /// Loads the index node whose entries start from `entries_offset`.
+    fn load(page_cache: &PageCache, entries_offset: usize, limit: usize) -> Result<Self> {
+        let count_limit: RawCountLimit = page_cache.pages().read_val(entries_offset)?;
+        if count_limit.limit as usize != limit
+            || count_limit.count == 0
+            || count_limit.count > count_limit.limit
+        {
+            return_errno_with_message!(Errno::EIO, "the index node is corrupted");
+        }
+
+        Ok(Self {
+            entries_offset,
+            count: count_limit.count as usize,
+            at: 0,
+        })
+    }
+
+    /// Moves to the last entry whose hash is not greater than `hash`.
+    ///
+    /// The first entry has no hash and covers the hashes less than the second one.
+    fn search(&mut self, page_cache: &PageCache, hash: u32) -> Result<()> {
+        let (mut low, mut high) = (1, self.count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entry(page_cache, mid)?.hash > hash {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        self.at = low - 1;
+        Ok(())
+    }
+
+    /// Returns the hash of the current entry.
+    fn hash(&self, page_cache: &PageCache) -> Result<u32> {
+        debug_assert!(self.at > 0);
+        Ok(self.entry(page_cache, self.at)?.hash)
+    }
+
+    /// Returns the offset of the block that the current entry points to.
+    fn block_offset(&self, page_cache: &PageCache) -> Result<usize> {
+        let block = (self.entry(page_cache, self.at)?.block & BLOCK_MASK) as usize;
+        // The root block cannot be a child.
+        if block == 0 || (block + 1) * BLOCK_SIZE > page_cache.pages().size() {
+            return_errno_with_message!(Errno::EIO, "the index entry is corrupted");
+        }
+        Ok(block * BLOCK_SIZE)
+    }
+
+    fn entry(&self, page_cache: &PageCache, idx: usize) -> Result<RawIndexEntry> {
+        let offset = self.entries_offset + idx * INDEX_ENTRY_SIZE;
+        Ok(page_cache.pages().read_val(offset)?)
+    }
+}
+
+/// The information of the index in the root block.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawRootInfo {
+    reserved_zero: u32,
+    /// The hash function of the index.
+    hash_version: u8,
+    /// The length of the root information, which is 8.
+    info_length: u8,
+    /// The levels of the index nodes below the root.
+    indirect_levels: u8,
+    unused_flags: u8,
+}
+
+/// The count and limit of the index entries, which overlaps the hash of the first entry.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawCountLimit {
+    /// The maximum number of the index entries.
+    limit: u16,
+    /// The number of the index entries.
+    count: u16,
+}
+
+/// The index entry, which maps the hashes starting from `hash` to the block `block`.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Pod)]
+struct RawIndexEntry {
+    hash: u32,
+    block: u32,
+}
+
+/// The hash function of the index.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromInt)]
+enum HashVersion {
+    Legacy = 0,
+    HalfMd4 = 1,
+    Tea = 2,
+    LegacyUnsigned = 3,
+    HalfMd4Unsigned = 4,
+    TeaUnsigned = 5,
+    SipHash = 6,
+}
+
+impl HashVersion {
+    /// Returns the version that treats the characters as unsigned.
+    fn to_unsigned(self) -> Self {
+        match self {
+            Self::Legacy => Self::LegacyUnsigned,
+            Self::HalfMd4 => Self::HalfMd4Unsigned,
+            Self::Tea => Self::TeaUnsigned,
+            _ => self,
+        }
+    }
+}
+
+/// Computes the hash of the name, which is compatible with Linux.
+fn dx_hash(name: &[u8], hash_version: HashVersion, seed: [u32; 4]) -> Result<u32> {
+    let mut buf = if seed == [0; 4] { DEFAULT_SEED } else { seed };
+
+    let hash = match hash_version {
+        HashVersion::Legacy => legacy_hash(name, false),
+        HashVersion::LegacyUnsigned => legacy_hash(name, true),
+        HashVersion::HalfMd4 | HashVersion::HalfMd4Unsigned => {
+            let is_unsigned = hash_version == HashVersion::HalfMd4Unsigned;
+            let mut input = [0u32; 8];
+            let mut msg = name;
+            while !msg.is_empty() {
+                str_to_hash_buf(msg, is_unsigned, &mut input);
+                half_md4_transform(&mut buf, &input);
+                msg = &msg[msg.len().min(32)..];
+            }
+            buf[1]
+        }
+        HashVersion::Tea | HashVersion::TeaUnsigned => {
+            let is_unsigned = hash_version == HashVersion::TeaUnsigned;
+            let mut input = [0u32; 4];
+            let mut msg = name;
+            while !msg.is_empty() {
+                str_to_hash_buf(msg, is_unsigned, &mut input);
+                tea_transform(&mut buf, &input);
+                msg = &msg[msg.len().min(16)..];
+            }
+            buf[0]
+        }
+        HashVersion::SipHash => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the casefolded hash is unsupported")
+        }
+    };
+
+    // The largest hash is reserved as the end-of-directory mark.
+    let hash = hash & !1;
+    if hash == 0xFFFF_FFFE {
+        Ok(0xFFFF_FFFC)
+    } else {
+        Ok(hash)
+    }
+}
+
+fn legacy_hash(name: &[u8], is_unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3fe2d_u32, 0x37abe8f9_u32);
+    for &byte in name {
+        let ch = if is_unsigned {
+            byte as i32
+        } else {
+            byte as i8 as i32
+        };
+        let mut hash = hash1.wrapping_add(hash0 ^ ch.wrapping_mul(7152373) as u32);
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs the leading bytes of `msg` into `buf`, padding with the length of `msg`.
+fn str_to_hash_buf(msg: &[u8], is_unsigned: bool, buf: &mut [u32]) {
+    let mut pad = msg.len() as u32 | (msg.len() as u32) << 8;
+    pad |= pad << 16;
+
+    let len = msg.len().min(buf.len() * 4);
+    let mut val = pad;
+    for (idx, &byte) in msg[..len].iter().enumerate() {
+        let ch = if is_unsigned {
+            byte as u32
+        } else {
+            byte as i8 as u32
+        };
+        val = ch.wrapping_add(val << 8);
+        if idx % 4 == 3 {
+            buf[idx / 4] = val;
+            val = pad;
+        }
+    }
+
+    let filled = len / 4;
+    if filled < buf.len() {
+        buf[filled] = val;
+        buf[filled + 1..].fill(pad);
+    }
+}
+
+/// The basic MD4 transform with half of the rounds.
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K2: u32 = 0x5A827999;
+    const K3: u32 = 0x6ED9EBA1;
+
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+    let mut state = *buf;
+    let mut round =
+        |func: &dyn Fn(u32, u32, u32) -> u32, order: [usize; 8], k: u32, shifts: [u32; 4]| {
+            for (step, &idx) in order.iter().enumerate() {
+                // The updated word rotates as a, d, c, b.
+                let target = (4 - step % 4) % 4;
+                let value = func(
+                    state[(target + 1) % 4],
+                    state[(target + 2) % 4],
+                    state[(target + 3) % 4],
+                );
+                state[target] = state[target]
+                    .wrapping_add(value)
+                    .wrapping_add(input[idx].wrapping_add(k))
+                    .rotate_left(shifts[step % 4]);
+            }
+        };
+    round(&f, [0, 1, 2, 3, 4, 5, 6, 7], 0, [3, 7, 11, 19]);
+    round(&g, [1, 3, 5, 7, 0, 2, 4, 6], K2, [3, 5, 9, 13]);
+    round(&h, [3, 7, 2, 6, 1, 5, 0, 4], K3, [3, 9, 11, 15]);
+
+    for (word, value) in buf.iter_mut().zip(state) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// The Tiny Encryption Algorithm transform.
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9E3779B9;
+
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum = 0u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            ((b1 << 4).wrapping_add(a)) ^ b1.wrapping_add(sum) ^ ((b1 >> 5).wrapping_add(b)),
+        );
+        b1 = b1.wrapping_add(
+            ((b0 << 4).wrapping_add(c)) ^ b0.wrapping_add(sum) ^ ((b0 >> 5).wrapping_add(d)),
+        );
+    }
+
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
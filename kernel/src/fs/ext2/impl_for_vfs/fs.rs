@@ -5,6 +5,7 @@ use ostd::sync::RwMutexReadGuard;
 use crate::{
     fs::{
         ext2::{utils::Dirty, Ext2, SuperBlock as Ext2SuperBlock, MAGIC_NUM as EXT2_MAGIC},
+        quota::Quota,
         utils::{FileSystem, FsFlags, Inode, SuperBlock, NAME_MAX},
     },
     prelude::*,
@@ -12,6 +13,8 @@ use crate::{
 
 impl FileSystem for Ext2 {
     fn sync(&self) -> Result<()> {
+        // The quotas are written back first, since they are stored in the files.
+        self.quota().sync()?;
         self.sync_all_inodes()?;
         self.sync_metadata()?;
 
@@ -30,22 +33,33 @@ impl FileSystem for Ext2 {
     fn flags(&self) -> FsFlags {
         FsFlags::empty()
     }
+
+    fn quota(&self) -> Option<&Quota> {
+        Some(self.quota())
+    }
 }
 
 impl From<RwMutexReadGuard<'_, Dirty<Ext2SuperBlock>>> for SuperBlock {
     fn from(ext2_sb: RwMutexReadGuard<Dirty<Ext2SuperBlock>>) -> Self {
+        // Like Linux, the file system ID is derived from the UUID.
+        let uuid = ext2_sb.uuid();
+        let fsid = u64::from_le_bytes(uuid[..8].try_into().unwrap())
+            ^ u64::from_le_bytes(uuid[8..].try_into().unwrap());
+
         Self {
             magic: EXT2_MAGIC as _,
             bsize: ext2_sb.block_size(),
             blocks: ext2_sb.total_blocks() as _,
             bfree: ext2_sb.free_blocks_count() as _,
-            bavail: ext2_sb.free_blocks_count() as _,
+            bavail: ext2_sb
+                .free_blocks_count()
+                .saturating_sub(ext2_sb.reserved_blocks_count()) as _,
             files: ext2_sb.total_inodes() as _,
             ffree: ext2_sb.free_inodes_count() as _,
-            fsid: 0, // TODO
+            fsid,
             namelen: NAME_MAX,
             frsize: ext2_sb.fragment_size(),
-            flags: 0, // TODO
+            flags: 0,
         }
     }
 }
@@ -8,10 +8,11 @@ use aster_rights::Full;
 
 use crate::{
     fs::{
-        ext2::{FilePerm, Inode as Ext2Inode},
+        ext2::{FileFlags, FilePerm, Inode as Ext2Inode},
         utils::{
-            DirentVisitor, Extension, FallocMode, FileSystem, Inode, InodeMode, InodeType,
-            IoctlCmd, Metadata, MknodType, XattrName, XattrNamespace, XattrSetFlags,
+            DirentVisitor, ExtendedMetadata, Extension, FallocMode, FileAttributes, FileSystem,
+            Inode, InodeFlags, InodeMode, InodeType, IoctlCmd, Metadata, MknodType, XattrName,
+            XattrNamespace, XattrSetFlags,
         },
     },
     prelude::*,
@@ -32,6 +33,38 @@ impl Inode for Ext2Inode {
         self.metadata()
     }
 
+    fn extended_metadata(&self) -> ExtendedMetadata {
+        // Ext2 does not record the birth time.
+        const FLAGS_TO_ATTRIBUTES: [(FileFlags, FileAttributes); 5] = [
+            (FileFlags::COMPRESS, FileAttributes::COMPRESSED),
+            (FileFlags::IMMUTABLE, FileAttributes::IMMUTABLE),
+            (FileFlags::APPEND_ONLY, FileAttributes::APPEND),
+            (FileFlags::NO_DUMP, FileAttributes::NODUMP),
+            (FileFlags::ENCRYPT, FileAttributes::ENCRYPTED),
+        ];
+
+        let file_flags = self.file_flags();
+        let mut metadata = ExtendedMetadata::default();
+        for (flag, attribute) in FLAGS_TO_ATTRIBUTES {
+            metadata.attributes_mask |= attribute;
+            if file_flags.contains(flag) {
+                metadata.attributes |= attribute;
+            }
+        }
+        metadata
+    }
+
+    fn flags(&self) -> InodeFlags {
+        // The values of the file flags are the same as those of the inode flags.
+        InodeFlags::from_bits_truncate(self.file_flags().bits())
+    }
+
+    fn set_flags(&self, flags: InodeFlags) -> Result<()> {
+        let mask = FileFlags::from_bits_truncate(InodeFlags::all().bits());
+        self.set_file_flags(mask, FileFlags::from_bits_truncate(flags.bits()));
+        Ok(())
+    }
+
     fn atime(&self) -> Duration {
         self.atime()
     }
@@ -78,8 +111,7 @@ impl Inode for Ext2Inode {
     }
 
     fn set_owner(&self, uid: Uid) -> Result<()> {
-        self.set_uid(uid.into());
-        Ok(())
+        self.set_uid(uid.into())
     }
 
     fn group(&self) -> Result<Gid> {
@@ -87,8 +119,7 @@ impl Inode for Ext2Inode {
     }
 
     fn set_group(&self, gid: Gid) -> Result<()> {
-        self.set_gid(gid.into());
-        Ok(())
+        self.set_gid(gid.into())
     }
 
     fn page_cache(&self) -> Option<Vmo<Full>> {
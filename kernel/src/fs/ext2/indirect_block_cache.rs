@@ -117,7 +117,7 @@ impl IndirectBlockCache {
                     Segment::<()>::from(block.frame.clone()).into(),
                     BioDirection::ToDevice,
                 );
-                bio_waiter.concat(self.fs().write_blocks_async(bid, bio_segment)?);
+                bio_waiter.concat(self.fs().write_metadata_async(bid, bio_segment)?);
             }
         }
 
@@ -182,6 +182,23 @@ impl IndirectBlock {
         self.state = State::Dirty;
         Ok(())
     }
+
+    /// Reads a value at a specified byte `offset`.
+    pub fn read_val<T: Pod>(&self, offset: usize) -> Result<T> {
+        assert!(self.state != State::Uninit);
+        let val = self.frame.read_val(offset)?;
+        Ok(val)
+    }
+
+    /// Writes a value at a specified byte `offset`.
+    ///
+    /// After a successful write operation, the block's state will be marked as dirty.
+    pub fn write_val<T: Pod>(&mut self, offset: usize, val: &T) -> Result<()> {
+        assert!(self.state != State::Uninit);
+        self.frame.write_val(offset, val)?;
+        self.state = State::Dirty;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -12,7 +12,9 @@ use ostd::{const_assert, mm::UntypedMem};
 use super::{
     block_ptr::{BidPath, BlockPtrs, Ext2Bid, BID_SIZE, MAX_BLOCK_PTRS},
     dir::{DirEntryHeader, DirEntryItem, DirEntryReader, DirEntryWriter},
+    extent::{self, ExtentTree, Mapping},
     fs::Ext2,
+    htree,
     indirect_block_cache::{IndirectBlock, IndirectBlockCache},
     prelude::*,
     utils::now,
@@ -21,6 +23,7 @@ use super::{
 use crate::{
     fs::{
         path::{is_dot, is_dot_or_dotdot, is_dotdot},
+        quota::QuotaIds,
         utils::{
             Extension, FallocMode, Inode as _, InodeMode, Metadata, Permission, XattrName,
             XattrNamespace, XattrSetFlags,
@@ -144,18 +147,29 @@ impl Inode {
             return_errno_with_message!(Errno::ENOENT, "dir removed");
         }
 
-        let inode = self
-            .fs()
-            .create_inode(self.block_group_idx, inode_type, file_perm)?;
+        let project_id = if inner.file_flags().contains(FileFlags::PROJ_INHERIT) {
+            inner.project_id()
+        } else {
+            0
+        };
+        let fs = self.fs();
+        let inode = fs.create_inode(self.block_group_idx, inode_type, file_perm, project_id)?;
         let is_dir = inode_type == InodeType::Dir;
+        let quota_ids = inode.quota_ids();
+        if let Err(e) = fs.quota().alloc_inode(inode.ino as _, &quota_ids) {
+            fs.free_inode(inode.ino, is_dir).unwrap();
+            return Err(e);
+        }
         if let Err(e) = inode.init(self.ino) {
-            self.fs().free_inode(inode.ino, is_dir).unwrap();
+            fs.quota().free_inode(inode.ino as _, &quota_ids);
+            fs.free_inode(inode.ino, is_dir).unwrap();
             return Err(e);
         }
 
         let mut inner = inner.upgrade();
         if let Err(e) = inner.append_new_entry(inode.ino, inode_type, name, true) {
-            self.fs().free_inode(inode.ino, is_dir).unwrap();
+            fs.quota().free_inode(inode.ino as _, &quota_ids);
+            fs.free_inode(inode.ino, is_dir).unwrap();
             return Err(e);
         }
 
@@ -615,14 +629,15 @@ impl Inode {
 
             let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
                 let mut dir_entry_reader = DirEntryReader::new(&inner.page_cache, *offset);
-                for dir_entry in dir_entry_reader.iter_entries() {
+                for entry in dir_entry_reader.iter_entries() {
+                    let (entry_offset, dir_entry) = entry?;
                     visitor.visit(
                         dir_entry.name(),
                         dir_entry.ino() as u64,
                         dir_entry.type_(),
                         dir_entry.record_len(),
                     )?;
-                    *offset += dir_entry.record_len();
+                    *offset = entry_offset + dir_entry.record_len();
                 }
 
                 Ok(())
@@ -635,8 +650,6 @@ impl Inode {
             }?
         };
 
-        self.set_atime(now());
-
         Ok(offset_read)
     }
 
@@ -684,8 +697,6 @@ impl Inode {
 
         let bytes_read = self.inner.read().read_at(offset, writer)?;
 
-        self.set_atime(now());
-
         Ok(bytes_read)
     }
 
@@ -700,8 +711,6 @@ impl Inode {
 
         let bytes_read = self.inner.read().read_direct_at(offset, writer)?;
 
-        self.set_atime(now());
-
         Ok(bytes_read)
     }
 
@@ -749,13 +758,36 @@ impl Inode {
         Ok(bytes_written)
     }
 
+    /// Returns the device block ranges that the file blocks are mapped to, in file order.
+    ///
+    /// This is used by the journal, whose blocks are accessed bypassing the inode.
+    pub(super) fn device_ranges(&self) -> Result<Vec<Range<Ext2Bid>>> {
+        let inner = self.inner.read();
+        let blocks_count = inner.blocks_count();
+        if blocks_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let block_manager = &inner.inode_impl.block_manager;
+        let mut device_ranges = Vec::new();
+        for device_range in DeviceRangeReader::new(block_manager, 0..blocks_count)? {
+            if device_range.start == 0 {
+                return_errno_with_message!(Errno::EIO, "the file has holes");
+            }
+            device_ranges.push(device_range);
+        }
+        Ok(device_ranges)
+    }
+
     pub fn sync_all(&self) -> Result<()> {
-        let mut inner = self.inner.write();
-        inner.sync_data()?;
-        inner.sync_metadata()?;
+        // The xattrs are written back first, since the inode may be updated if the xattr
+        // block is reallocated.
         if let Some(xattr) = self.xattr.as_ref() {
             xattr.flush()?;
         }
+        let mut inner = self.inner.write();
+        inner.sync_data()?;
+        inner.sync_metadata()?;
         Ok(())
     }
 
@@ -765,16 +797,37 @@ impl Inode {
         inner.set_ctime(now());
     }
 
-    pub fn set_uid(&self, uid: u32) {
+    pub fn set_uid(&self, uid: u32) -> Result<()> {
         let mut inner = self.inner.write();
+        let old_ids = inner.quota_ids();
+        let new_ids = QuotaIds { uid, ..old_ids };
+        self.fs()
+            .quota()
+            .transfer(self.ino as _, &old_ids, &new_ids, inner.charged_space())?;
+
         inner.set_uid(uid);
         inner.set_ctime(now());
+        Ok(())
     }
 
-    pub fn set_gid(&self, gid: u32) {
+    pub fn set_gid(&self, gid: u32) -> Result<()> {
         let mut inner = self.inner.write();
+        let old_ids = inner.quota_ids();
+        let new_ids = QuotaIds { gid, ..old_ids };
+        self.fs()
+            .quota()
+            .transfer(self.ino as _, &old_ids, &new_ids, inner.charged_space())?;
+
         inner.set_gid(gid);
         inner.set_ctime(now());
+        Ok(())
+    }
+
+    /// Sets the file flags in `mask` to those in `flags`, leaving the others unchanged.
+    pub fn set_file_flags(&self, mask: FileFlags, flags: FileFlags) {
+        let mut inner = self.inner.write();
+        inner.set_file_flags(mask, flags);
+        inner.set_ctime(now());
     }
 
     pub fn extension(&self) -> &Extension {
@@ -811,6 +864,25 @@ impl Inode {
                 Ok(())
             }
             FallocMode::AllocateKeepSize => Ok(()),
+            FallocMode::ZeroRange | FallocMode::ZeroRangeKeepSize => {
+                let mut inner = self.inner.write();
+
+                let mut file_size = inner.file_size();
+                let end_offset = offset + len;
+                if mode == FallocMode::ZeroRange && end_offset > file_size {
+                    inner.resize(end_offset)?;
+                    file_size = end_offset;
+                }
+                // Like Linux, the range beyond the end of the file is ignored with `KEEP_SIZE`.
+                let end_offset = end_offset.min(file_size);
+                if offset >= end_offset {
+                    return Ok(());
+                }
+
+                // Ext2 has no unwritten extents, so the blocks are zeroed in place.
+                inner.page_cache.fill_zeros(offset..end_offset)?;
+                Ok(())
+            }
             _ => {
                 return_errno_with_message!(
                     Errno::EOPNOTSUPP,
@@ -831,7 +903,9 @@ impl Inode {
             "xattr is not supported on the file type",
         ))?;
         self.check_permission(Permission::MAY_WRITE)?;
-        xattr.set(name, value_reader, flags)
+        xattr.set(name, value_reader, flags)?;
+        self.set_ctime(now());
+        Ok(())
     }
 
     pub fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
@@ -859,7 +933,9 @@ impl Inode {
             "xattr is not supported on the file type",
         ))?;
         self.check_permission(Permission::MAY_WRITE)?;
-        self.xattr.as_ref().unwrap().remove(name)
+        xattr.remove(name)?;
+        self.set_ctime(now());
+        Ok(())
     }
 }
 
@@ -869,6 +945,8 @@ impl Inode {
     pub fn file_perm(&self) -> FilePerm;
     pub fn uid(&self) -> u32;
     pub fn gid(&self) -> u32;
+    pub fn project_id(&self) -> u32;
+    pub fn quota_ids(&self) -> QuotaIds;
     pub fn file_flags(&self) -> FileFlags;
     pub fn hard_links(&self) -> u16;
     pub fn blocks_count(&self) -> Ext2Bid;
@@ -1083,10 +1161,26 @@ impl InodeInner {
     }
 
     pub fn contains_entry(&self, name: &str) -> bool {
-        DirEntryReader::new(&self.page_cache, 0).contains_entry(name)
+        self.find_entry_item(name).is_some()
     }
 
     pub fn find_entry_item(&self, name: &str) -> Option<DirEntryItem> {
+        // Looks up the hash index first. If the index is corrupted or unsupported,
+        // falls back to the linear search, since the index nodes are also valid
+        // directory blocks.
+        if self.file_flags().contains(FileFlags::INDEX_DIR) && !is_dot_or_dotdot(name) {
+            let fs = self.inode_impl.fs();
+            let lookup_result = htree::lookup(
+                &self.page_cache,
+                name,
+                fs.hash_seed(),
+                fs.is_hash_unsigned(),
+            );
+            if let Ok(entry_item) = lookup_result {
+                return entry_item;
+            }
+        }
+
         DirEntryReader::new(&self.page_cache, 0).find_entry_item(name)
     }
 
@@ -1101,6 +1195,7 @@ impl InodeInner {
         name: &str,
         check_existence: bool,
     ) -> Result<()> {
+        self.clear_dir_index();
         let entry_header = DirEntryHeader::new(ino, inode_type, name.len());
         DirEntryWriter::new(&self.page_cache, 0).append_new_entry(
             entry_header,
@@ -1123,6 +1218,7 @@ impl InodeInner {
     }
 
     pub fn remove_entry_at(&mut self, name: &str, offset: usize) -> Result<()> {
+        self.clear_dir_index();
         let removed_entry = DirEntryWriter::new(&self.page_cache, offset).remove_entry(name)?;
         let file_size = self.file_size();
         let page_cache_size = self.page_cache.pages().size();
@@ -1136,6 +1232,7 @@ impl InodeInner {
     }
 
     pub fn rename_entry_at(&mut self, old_name: &str, new_name: &str, offset: usize) -> Result<()> {
+        self.clear_dir_index();
         DirEntryWriter::new(&self.page_cache, offset).rename_entry(old_name, new_name)?;
         let file_size = self.file_size();
         let page_cache_size = self.page_cache.pages().size();
@@ -1145,6 +1242,14 @@ impl InodeInner {
         Ok(())
     }
 
+    /// Clears the hash index of the directory before the entries are modified.
+    ///
+    /// The index is not maintained on modification, so it is dropped and the directory
+    /// falls back to a linear one, as Linux does with a directory whose index is invalid.
+    fn clear_dir_index(&mut self) {
+        self.inode_impl.clear_file_flags(FileFlags::INDEX_DIR);
+    }
+
     pub fn set_parent_ino(&mut self, parent_ino: u32) -> Result<()> {
         let mut entry_item = self.find_entry_item("..").unwrap();
         entry_item.set_ino(parent_ino);
@@ -1171,7 +1276,11 @@ impl InodeInner {
     pub fn set_uid(&mut self, uid: u32);
     pub fn gid(&self) -> u32;
     pub fn set_gid(&mut self, gid: u32);
+    pub fn project_id(&self) -> u32;
+    pub fn quota_ids(&self) -> QuotaIds;
+    pub fn charged_space(&self) -> u64;
     pub fn file_flags(&self) -> FileFlags;
+    pub fn set_file_flags(&mut self, mask: FileFlags, flags: FileFlags);
     pub fn hard_links(&self) -> u16;
     pub fn inc_hard_links(&mut self);
     pub fn dec_hard_links(&mut self);
@@ -1203,6 +1312,8 @@ impl InodeImpl {
             nblocks: AtomicUsize::new(desc.blocks_count() as _),
             block_ptrs: RwMutex::new(desc.block_ptrs),
             indirect_blocks: RwMutex::new(IndirectBlockCache::new(fs.clone())),
+            uses_extents: desc.flags.contains(FileFlags::EXTENTS),
+            is_dir: desc.type_ == InodeType::Dir,
             fs,
         };
         Self {
@@ -1254,6 +1365,42 @@ impl InodeImpl {
         self.desc.gid = gid;
     }
 
+    pub fn project_id(&self) -> u32 {
+        self.desc.project_id
+    }
+
+    /// Returns the owners that the inode is charged to.
+    pub fn quota_ids(&self) -> QuotaIds {
+        QuotaIds {
+            uid: self.desc.uid,
+            gid: self.desc.gid,
+            project_id: self.desc.project_id,
+        }
+    }
+
+    /// Returns the space charged to the owners of the inode.
+    ///
+    /// Only the data blocks are charged.
+    pub fn charged_space(&self) -> u64 {
+        self.blocks_count() as u64 * BLOCK_SIZE as u64
+    }
+
+    pub fn clear_file_flags(&mut self, flags: FileFlags) {
+        // Avoids dirtying the descriptor if the flags are already cleared.
+        if self.desc.flags.intersects(flags) {
+            self.desc.flags.remove(flags);
+        }
+    }
+
+    /// Sets the file flags in `mask` to those in `flags`, leaving the others unchanged.
+    pub fn set_file_flags(&mut self, mask: FileFlags, flags: FileFlags) {
+        let new_flags = (self.desc.flags - mask) | (flags & mask);
+        // Avoids dirtying the descriptor if the flags are not changed.
+        if new_flags != self.desc.flags {
+            self.desc.flags = new_flags;
+        }
+    }
+
     pub fn file_flags(&self) -> FileFlags {
         self.desc.flags
     }
@@ -1348,9 +1495,9 @@ impl InodeImpl {
             self.resize(0)?;
             // Adds the check here to prevent double-free.
             if !self.is_freed {
-                inode
-                    .fs()
-                    .free_inode(inode.ino(), self.desc.type_ == InodeType::Dir)?;
+                let fs = inode.fs();
+                fs.free_inode(inode.ino(), self.desc.type_ == InodeType::Dir)?;
+                fs.quota().free_inode(inode.ino() as _, &self.quota_ids());
                 if let Some(xattr) = &inode.xattr {
                     xattr.free()?;
                 }
@@ -1387,10 +1534,19 @@ impl InodeImpl {
 
         // Expands block count if necessary
         if new_blocks > old_blocks {
-            if new_blocks - old_blocks > self.fs().super_block().free_blocks_count() {
+            let fs = self.fs();
+            if new_blocks - old_blocks > fs.super_block().free_blocks_count() {
                 return_errno_with_message!(Errno::ENOSPC, "not enough free blocks");
             }
-            self.expand_blocks(old_blocks..new_blocks)?;
+
+            let ino = self.inode().ino() as u64;
+            let quota_ids = self.quota_ids();
+            let space = (new_blocks - old_blocks) as u64 * BLOCK_SIZE as u64;
+            fs.quota().alloc_space(ino, &quota_ids, space)?;
+            if let Err(e) = self.expand_blocks(old_blocks..new_blocks) {
+                fs.quota().free_space(ino, &quota_ids, space);
+                return Err(e);
+            }
         }
 
         // Expands the size
@@ -1421,6 +1577,18 @@ impl InodeImpl {
     /// isn't enough consecutive space available or if there is a necessity to allocate
     /// indirect blocks.
     fn try_expand_blocks(&mut self, range: Range<Ext2Bid>) -> Result<Ext2Bid> {
+        // Calculates the block_group_idx to advise the filesystem on which group
+        // to prioritize for allocation.
+        let block_group_idx = self
+            .last_alloc_device_bid
+            .map_or(self.inode().block_group_idx, |id| {
+                ((id + 1) / self.fs().blocks_per_group()) as usize
+            });
+
+        if self.block_manager.uses_extents {
+            return self.try_expand_extents(range, block_group_idx);
+        }
+
         // Calculates the maximum number of consecutive blocks that can be allocated in
         // this round, as well as the number of additional indirect blocks required for
         // the allocation.
@@ -1442,14 +1610,6 @@ impl InodeImpl {
             (max_cnt, indirect_cnt)
         };
 
-        // Calculates the block_group_idx to advise the filesystem on which group
-        // to prioritize for allocation.
-        let block_group_idx = self
-            .last_alloc_device_bid
-            .map_or(self.inode().block_group_idx, |id| {
-                ((id + 1) / self.fs().blocks_per_group()) as usize
-            });
-
         // Allocates the blocks only, no indirect blocks are required.
         if indirect_cnt == 0 {
             let device_range = self
@@ -1645,6 +1805,11 @@ impl InodeImpl {
         // Shrinks block count if necessary
         if new_blocks < old_blocks {
             self.shrink_blocks(new_blocks..old_blocks);
+
+            let space = (old_blocks - new_blocks) as u64 * BLOCK_SIZE as u64;
+            self.fs()
+                .quota()
+                .free_space(self.inode().ino() as _, &self.quota_ids(), space);
         }
 
         // Shrinks the size
@@ -1658,6 +1823,49 @@ impl InodeImpl {
             .store(self.blocks_count() as _, Ordering::Release);
     }
 
+    /// Attempts to expand a range of blocks mapped by extents and returns the number of
+    /// blocks successfully allocated.
+    fn try_expand_extents(
+        &mut self,
+        range: Range<Ext2Bid>,
+        block_group_idx: usize,
+    ) -> Result<Ext2Bid> {
+        let fs = self.fs();
+        let device_range = fs
+            .alloc_blocks(block_group_idx, range.len() as Ext2Bid)
+            .ok_or_else(|| Error::new(Errno::ENOSPC))?;
+
+        let mapped_cnt = {
+            let mut block_ptrs = self.block_manager.block_ptrs.write();
+            let mut indirect_blocks = self.block_manager.indirect_blocks.write();
+            let mapped_cnt = ExtentTree::new(&mut *block_ptrs, &mut *indirect_blocks).append(
+                range.start,
+                device_range.clone(),
+                &fs,
+                block_group_idx,
+            );
+            self.desc.block_ptrs = *block_ptrs;
+            mapped_cnt
+        };
+        let mapped_cnt = match mapped_cnt {
+            Ok(mapped_cnt) => mapped_cnt,
+            Err(e) => {
+                fs.free_blocks(device_range).unwrap();
+                return Err(e);
+            }
+        };
+
+        // Frees the blocks that cannot be mapped by the extent in this round.
+        let mapped_end = device_range.start + mapped_cnt;
+        if mapped_end < device_range.end {
+            fs.free_blocks(mapped_end..device_range.end).unwrap();
+        }
+
+        self.desc.blocks_count = range.start + mapped_cnt;
+        self.last_alloc_device_bid = Some(mapped_end - 1);
+        Ok(mapped_cnt)
+    }
+
     /// Shrinks inode blocks.
     ///
     /// After the reduction, the block count will be decreased to `range.start`.
@@ -1688,6 +1896,17 @@ impl InodeImpl {
     /// Note that the returned number may be less than the requested range if needs
     /// to free the indirect blocks that are no longer required.
     fn try_shrink_blocks(&mut self, range: Range<Ext2Bid>) -> Ext2Bid {
+        // The extent tree frees the unmapped blocks and the empty nodes by itself.
+        if self.block_manager.uses_extents {
+            let mut block_ptrs = self.block_manager.block_ptrs.write();
+            let mut indirect_blocks = self.block_manager.indirect_blocks.write();
+            ExtentTree::new(&mut *block_ptrs, &mut *indirect_blocks)
+                .truncate(range.start, &self.fs())
+                .unwrap();
+            self.desc.block_ptrs = *block_ptrs;
+            return range.len() as Ext2Bid;
+        }
+
         // Calculates the maximum range of blocks that can be freed in this round.
         let range = {
             let max_cnt = (range.len() as Ext2Bid)
@@ -1832,6 +2051,10 @@ struct InodeBlockManager {
     /// frequent reads access the `InodeDesc` copy without locking.
     block_ptrs: RwMutex<BlockPtrs>,
     indirect_blocks: RwMutex<IndirectBlockCache>,
+    /// Whether the blocks are mapped by extents.
+    uses_extents: bool,
+    /// Whether the blocks belong to a directory.
+    is_dir: bool,
     fs: Weak<Ext2>,
 }
 
@@ -1845,15 +2068,22 @@ impl InodeBlockManager {
     ) -> Result<BioWaiter> {
         debug_assert!(nblocks * BLOCK_SIZE <= writer.avail());
         let mut bio_waiter = BioWaiter::new();
+        // Holds back the bios of the ranges so that they can be sorted and merged.
+        let fs = self.fs();
+        let _plug = fs.block_device().plug();
 
         for dev_range in DeviceRangeReader::new(self, bid..bid + nblocks as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             let range_nblocks = dev_range.len();
+            if start_bid == 0 {
+                writer.fill_zeros(range_nblocks * BLOCK_SIZE)?;
+                continue;
+            }
 
             let bio_segment = BioSegment::alloc(range_nblocks, BioDirection::FromDevice);
             bio_segment.reader().unwrap().read_fallible(writer)?;
 
-            let waiter = self.fs().read_blocks_async(start_bid, bio_segment)?;
+            let waiter = fs.read_blocks_async(start_bid, bio_segment)?;
             bio_waiter.concat(waiter);
         }
 
@@ -1872,6 +2102,10 @@ impl InodeBlockManager {
 
         for dev_range in DeviceRangeReader::new(self, bid..bid + 1 as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
+            if start_bid == 0 {
+                frame.writer().fill(0u8);
+                continue;
+            }
             // TODO: Should we allocate the bio segment from the pool on reads?
             // This may require an additional copy to the requested frame in the completion callback.
             let bio_segment = BioSegment::new_from_segment(
@@ -1894,15 +2128,20 @@ impl InodeBlockManager {
     ) -> Result<BioWaiter> {
         debug_assert_eq!(nblocks * BLOCK_SIZE, reader.remain());
         let mut bio_waiter = BioWaiter::new();
+        let fs = self.fs();
+        let _plug = fs.block_device().plug();
 
         for dev_range in DeviceRangeReader::new(self, bid..bid + nblocks as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             let range_nblocks = dev_range.len();
+            if start_bid == 0 {
+                return_errno_with_message!(Errno::EIO, "writing to holes is not supported");
+            }
 
             let bio_segment = BioSegment::alloc(range_nblocks, BioDirection::ToDevice);
             bio_segment.writer().unwrap().write_fallible(reader)?;
 
-            let waiter = self.fs().write_blocks_async(start_bid, bio_segment)?;
+            let waiter = self.write_device_blocks_async(start_bid, bio_segment)?;
             bio_waiter.concat(waiter);
         }
 
@@ -1921,19 +2160,37 @@ impl InodeBlockManager {
 
         for dev_range in DeviceRangeReader::new(self, bid..bid + 1 as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
+            if start_bid == 0 {
+                return_errno_with_message!(Errno::EIO, "writing to holes is not supported");
+            }
             let bio_segment = BioSegment::alloc(1, BioDirection::ToDevice);
             // This requires an additional copy to the pooled bio segment.
             bio_segment
                 .writer()
                 .unwrap()
                 .write_fallible(&mut frame.reader().to_fallible())?;
-            let waiter = self.fs().write_blocks_async(start_bid, bio_segment)?;
+            let waiter = self.write_device_blocks_async(start_bid, bio_segment)?;
             bio_waiter.concat(waiter);
         }
 
         Ok(bio_waiter)
     }
 
+    /// Writes the blocks to the device.
+    ///
+    /// The blocks of a directory are metadata, so they are written through the journal.
+    fn write_device_blocks_async(
+        &self,
+        bid: Ext2Bid,
+        bio_segment: BioSegment,
+    ) -> Result<BioWaiter> {
+        if self.is_dir {
+            self.fs().write_metadata_async(bid, bio_segment)
+        } else {
+            self.fs().write_blocks_async(bid, bio_segment)
+        }
+    }
+
     pub fn nblocks(&self) -> usize {
         self.nblocks.load(Ordering::Acquire)
     }
@@ -1964,11 +2221,14 @@ impl PageCacheBackend for InodeBlockManager {
 /// It calculates and returns the range of block IDs on the device that would map to
 /// the file's block range. This is useful for translating file-level block addresses
 /// to their locations on the physical storage device.
+///
+/// A range starting from zero represents a hole, i.e., the blocks that are not mapped.
 struct DeviceRangeReader<'a> {
     block_ptrs: RwMutexReadGuard<'a, BlockPtrs>,
     indirect_blocks: RwMutexWriteGuard<'a, IndirectBlockCache>,
     range: Range<Ext2Bid>,
     indirect_block: Option<IndirectBlock>,
+    uses_extents: bool,
 }
 
 impl<'a> DeviceRangeReader<'a> {
@@ -1985,8 +2245,11 @@ impl<'a> DeviceRangeReader<'a> {
             indirect_blocks: block_manager.indirect_blocks.write(),
             range,
             indirect_block: None,
+            uses_extents: block_manager.uses_extents,
         };
-        reader.update_indirect_block()?;
+        if !reader.uses_extents {
+            reader.update_indirect_block()?;
+        }
         Ok(reader)
     }
 
@@ -1995,6 +2258,10 @@ impl<'a> DeviceRangeReader<'a> {
     /// Note that the returned device range size may be smaller than the requested range
     /// due to possible inconsecutive block allocation.
     pub fn read(&mut self) -> Result<Range<Ext2Bid>> {
+        if self.uses_extents {
+            return self.read_extent();
+        }
+
         let bid_path = BidPath::from(self.range.start);
         let max_cnt = self
             .range
@@ -2034,6 +2301,19 @@ impl<'a> DeviceRangeReader<'a> {
         Ok(device_range)
     }
 
+    fn read_extent(&mut self) -> Result<Range<Ext2Bid>> {
+        let mapping = ExtentTree::new(&*self.block_ptrs, &mut *self.indirect_blocks)
+            .lookup(self.range.start)?;
+        let max_cnt = self.range.len() as Ext2Bid;
+        let device_range = match mapping {
+            Mapping::Mapped { device_bid, len } => device_bid..device_bid + len.min(max_cnt),
+            Mapping::Hole { len } => 0..len.min(max_cnt),
+        };
+
+        self.range.start += device_range.len() as Ext2Bid;
+        Ok(device_range)
+    }
+
     fn update_indirect_block(&mut self) -> Result<()> {
         let bid_path = BidPath::from(self.range.start);
         match bid_path {
@@ -2123,6 +2403,8 @@ pub(super) struct InodeDesc {
     block_ptrs: BlockPtrs,
     /// File or directory acl block.
     acl: Option<Bid>,
+    /// Project Id, which is stored in the extra fields of the raw inode.
+    project_id: u32,
 }
 
 impl TryFrom<RawInode> for InodeDesc {
@@ -2150,10 +2432,10 @@ impl TryFrom<RawInode> for InodeDesc {
                 .ok_or(Error::with_message(Errno::EINVAL, "invalid file flags"))?,
             block_ptrs: inode.block_ptrs,
             acl: match inode_type {
-                InodeType::File => Some(Bid::new(inode.file_acl as _)),
-                InodeType::Dir => Some(Bid::new(inode.size_high as _)),
+                InodeType::File | InodeType::Dir => Some(Bid::new(inode.file_acl as _)),
                 _ => None,
             },
+            project_id: 0,
         })
     }
 }
@@ -2180,9 +2462,22 @@ impl InodeDesc {
                 InodeType::File | InodeType::Dir => Some(Bid::new(0)),
                 _ => None,
             },
+            project_id: 0,
         })
     }
 
+    /// Sets the project ID, which is loaded from the extra fields of the raw inode.
+    pub fn set_project_id(&mut self, project_id: u32) {
+        self.project_id = project_id;
+    }
+
+    /// Makes the blocks of the new inode mapped by extents.
+    pub fn enable_extents(&mut self) {
+        debug_assert_eq!(self.blocks_count, 0);
+        self.flags.insert(FileFlags::EXTENTS);
+        extent::init_root(&mut self.block_ptrs);
+    }
+
     pub fn num_page_bytes(&self) -> usize {
         (self.blocks_count() as usize) * BLOCK_SIZE
     }
@@ -2280,6 +2575,24 @@ bitflags! {
         const DIR_SYNC = 1 << 16;
         /// Top of directory hierarchies.
         const TOP_DIR = 1 << 17;
+        /// Huge file, whose block count is in the unit of blocks.
+        const HUGE_FILE = 1 << 18;
+        /// Blocks are mapped by extents.
+        const EXTENTS = 1 << 19;
+        /// Verity protected file.
+        const VERITY = 1 << 20;
+        /// Inode used for a large xattr value.
+        const EA_INODE = 1 << 21;
+        /// Blocks allocated beyond the end of the file.
+        const EOF_BLOCKS = 1 << 22;
+        /// Direct access.
+        const DAX = 1 << 25;
+        /// Data stored in the inode.
+        const INLINE_DATA = 1 << 28;
+        /// Project ID inheritance.
+        const PROJ_INHERIT = 1 << 29;
+        /// Case-insensitive directory.
+        const CASEFOLD = 1 << 30;
         /// Reserved for ext2 lib.
         const RESERVED = 1 << 31;
     }
@@ -2346,11 +2659,11 @@ impl From<&InodeDesc> for RawInode {
             flags: inode.flags.bits(),
             block_ptrs: inode.block_ptrs,
             file_acl: match inode.acl {
-                Some(acl) if inode.type_ == InodeType::File => acl.to_raw() as u32,
+                Some(acl) => acl.to_raw() as u32,
                 _ => Default::default(),
             },
-            size_high: match inode.acl {
-                Some(acl) if inode.type_ == InodeType::Dir => acl.to_raw() as u32,
+            size_high: match inode.type_ {
+                InodeType::File => (inode.size >> 32) as u32,
                 _ => Default::default(),
             },
             os_dependent_2: Osd2 {
@@ -0,0 +1,649 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The journal of Ext3 and Ext4.
+//!
+//! The journal (a.k.a. JBD2) is a circular log stored in a hidden inode. The metadata blocks
+//! are written to the log, followed by a commit block, before they are written in place. If
+//! the system crashes while writing the blocks in place, the blocks are replayed from the log
+//! at the next mount, so the metadata is always consistent.
+//!
+//! The journal works in the ordered mode, i.e., the data blocks are written to the device
+//! before the metadata blocks referring to them are committed.
+//!
+//! Unlike Linux, the transactions are committed and checkpointed synchronously when the
+//! filesystem is synced. So the log is empty unless a commit is in progress, and the revoke
+//! records are never needed for the transactions written by us. But the revoke records written
+//! by Linux are respected during the recovery.
+
+use super::{block_ptr::Ext2Bid, prelude::*};
+
+/// The magic number of the journal blocks.
+const JBD2_MAGIC: u32 = 0xC03B3998;
+
+/// The journal block that describes the following blocks in the log.
+const DESCRIPTOR_BLOCK: u32 = 1;
+/// The journal block that marks the end of a transaction.
+const COMMIT_BLOCK: u32 = 2;
+/// The journal superblock, version 1.
+const SUPER_BLOCK_V1: u32 = 3;
+/// The journal superblock, version 2.
+const SUPER_BLOCK_V2: u32 = 4;
+/// The journal block that lists the revoked blocks.
+const REVOKE_BLOCK: u32 = 5;
+
+/// The size of the header of the journal blocks.
+const HEADER_SIZE: usize = 12;
+/// The size of the header of the revoke blocks.
+const REVOKE_HEADER_SIZE: usize = 16;
+/// The size of the UUID following the block tags.
+const UUID_SIZE: usize = 16;
+/// The size of the part of the journal superblock that is written back.
+const SUPER_BLOCK_SIZE: usize = 1024;
+
+/// The offset of `s_sequence` in the journal superblock.
+const SEQUENCE_OFFSET: usize = 0x18;
+/// The offset of `s_start` in the journal superblock.
+const START_OFFSET: usize = 0x1C;
+
+bitflags! {
+    /// The incompatible features of the journal.
+    struct JournalInCompatSet: u32 {
+        /// The journal has revoke blocks.
+        const REVOKE = 1 << 0;
+        /// The block numbers are 64-bit.
+        const BIT64 = 1 << 1;
+        /// The commit blocks can be written without waiting for the descriptor blocks.
+        const ASYNC_COMMIT = 1 << 2;
+        /// The journal blocks have checksums (version 2).
+        const CSUM_V2 = 1 << 3;
+        /// The journal blocks have checksums (version 3).
+        const CSUM_V3 = 1 << 4;
+        /// The journal has fast commits.
+        const FAST_COMMIT = 1 << 5;
+
+        /// The features that are supported.
+        const SUPPORTED = Self::REVOKE.bits | Self::BIT64.bits;
+    }
+}
+
+bitflags! {
+    /// The flags of the block tags in the descriptor blocks.
+    struct TagFlags: u16 {
+        /// The block starts with the magic number, which is zeroed in the log.
+        const ESCAPE = 1 << 0;
+        /// The tag is not followed by a UUID.
+        const SAME_UUID = 1 << 1;
+        /// The block is deleted by this transaction (not used).
+        const DELETED = 1 << 2;
+        /// The tag is the last one in the descriptor block.
+        const LAST_TAG = 1 << 3;
+    }
+}
+
+/// The journal.
+pub(super) struct Journal {
+    /// The device block IDs of the journal blocks.
+    blocks: Vec<Ext2Bid>,
+    /// The index of the first log block.
+    first: u32,
+    /// The number of the journal blocks that are used.
+    max_len: u32,
+    /// Whether the block numbers are 64-bit.
+    is_64bit: bool,
+    uuid: [u8; UUID_SIZE],
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    /// The bytes of the journal superblock.
+    super_block: Vec<u8>,
+    /// The sequence number of the next transaction.
+    sequence: u32,
+    /// The log block where the recovery starts, or 0 if the log is empty.
+    start: u32,
+    /// The metadata blocks to be committed.
+    pending_blocks: BTreeMap<Ext2Bid, Box<[u8]>>,
+}
+
+impl Journal {
+    /// Loads the journal, whose blocks are located at `device_ranges` of the block device.
+    pub fn load(block_device: &dyn BlockDevice, device_ranges: &[Range<Ext2Bid>]) -> Result<Self> {
+        let blocks: Vec<Ext2Bid> = device_ranges.iter().cloned().flatten().collect();
+        let Some(sb_bid) = blocks.first() else {
+            return_errno_with_message!(Errno::EINVAL, "the journal is empty");
+        };
+
+        let mut super_block = vec![0u8; SUPER_BLOCK_SIZE];
+        block_device.read_bytes(*sb_bid as usize * BLOCK_SIZE, &mut super_block)?;
+
+        let magic = read_be32(&super_block, 0x0);
+        let block_type = read_be32(&super_block, 0x4);
+        if magic != JBD2_MAGIC || !matches!(block_type, SUPER_BLOCK_V1 | SUPER_BLOCK_V2) {
+            return_errno_with_message!(Errno::EINVAL, "bad journal superblock");
+        }
+
+        let block_size = read_be32(&super_block, 0xC) as usize;
+        let max_len = read_be32(&super_block, 0x10);
+        let first = read_be32(&super_block, 0x14);
+        if block_size != BLOCK_SIZE
+            || max_len as usize > blocks.len()
+            || first == 0
+            || first >= max_len
+        {
+            return_errno_with_message!(Errno::EINVAL, "invalid journal layout");
+        }
+
+        let incompat = if block_type == SUPER_BLOCK_V2 {
+            let compat = read_be32(&super_block, 0x24);
+            let incompat = read_be32(&super_block, 0x28);
+            let ro_compat = read_be32(&super_block, 0x2C);
+            // The only compatible feature is the checksum of commit blocks.
+            if compat != 0 || ro_compat != 0 {
+                return_errno_with_message!(Errno::EINVAL, "unsupported journal features");
+            }
+            JournalInCompatSet::from_bits(incompat)
+                .filter(|incompat| JournalInCompatSet::SUPPORTED.contains(*incompat))
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported journal features"))?
+        } else {
+            JournalInCompatSet::empty()
+        };
+
+        let mut uuid = [0u8; UUID_SIZE];
+        uuid.copy_from_slice(&super_block[0x30..0x30 + UUID_SIZE]);
+
+        Ok(Self {
+            blocks,
+            first,
+            max_len,
+            is_64bit: incompat.contains(JournalInCompatSet::BIT64),
+            uuid,
+            inner: Mutex::new(Inner {
+                sequence: read_be32(&super_block, SEQUENCE_OFFSET),
+                start: read_be32(&super_block, START_OFFSET),
+                super_block,
+                pending_blocks: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Returns whether the log contains transactions.
+    pub fn is_dirty(&self) -> bool {
+        self.inner.lock().start != 0
+    }
+
+    /// Replays the committed transactions in the log, then empties the log.
+    ///
+    /// If `replays` is false, the transactions are discarded instead. This is what Linux does
+    /// if the superblock does not require a recovery.
+    pub fn recover(&self, block_device: &dyn BlockDevice, replays: bool) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.start == 0 {
+            return Ok(());
+        }
+
+        let start = inner.start;
+        let start_sequence = inner.sequence;
+        let end_sequence = self.do_pass(block_device, start, start_sequence, Pass::Scan)?;
+
+        if replays {
+            let mut revoked = BTreeMap::new();
+            self.do_pass(
+                block_device,
+                start,
+                start_sequence,
+                Pass::Revoke {
+                    end_sequence,
+                    revoked: &mut revoked,
+                },
+            )?;
+            self.do_pass(
+                block_device,
+                start,
+                start_sequence,
+                Pass::Replay {
+                    end_sequence,
+                    revoked: &revoked,
+                },
+            )?;
+            flush(block_device)?;
+        }
+
+        inner.sequence = end_sequence;
+        inner.start = 0;
+        self.write_super_block(block_device, &mut inner)?;
+        flush(block_device)
+    }
+
+    /// Walks through the log from the `start` block.
+    ///
+    /// For the scan pass, returns the sequence number of the first transaction that is not
+    /// committed.
+    fn do_pass(
+        &self,
+        block_device: &dyn BlockDevice,
+        start: u32,
+        start_sequence: u32,
+        mut pass: Pass,
+    ) -> Result<u32> {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        let mut log_block = start;
+        let mut sequence = start_sequence;
+        // Each block is visited at most once, even if the log is corrupted.
+        let mut nr_visited = 0;
+
+        loop {
+            if let Pass::Revoke { end_sequence, .. } | Pass::Replay { end_sequence, .. } = pass {
+                if sequence == end_sequence {
+                    break;
+                }
+            }
+            if nr_visited >= self.max_len - self.first {
+                break;
+            }
+
+            self.read_log_block(block_device, log_block, &mut block)?;
+            nr_visited += 1;
+            if read_be32(&block, 0x0) != JBD2_MAGIC || read_be32(&block, 0x8) != sequence {
+                break;
+            }
+
+            match read_be32(&block, 0x4) {
+                DESCRIPTOR_BLOCK => {
+                    let tags = self.parse_tags(&block);
+                    if let Pass::Replay { revoked, .. } = &pass {
+                        let mut data = vec![0u8; BLOCK_SIZE];
+                        for (i, (target, flags)) in tags.iter().enumerate() {
+                            let is_revoked = revoked.get(target).is_some_and(|revoked_sequence| {
+                                !seq_before(*revoked_sequence, sequence)
+                            });
+                            if is_revoked {
+                                continue;
+                            }
+                            let Ok(target) = Ext2Bid::try_from(*target) else {
+                                return_errno_with_message!(Errno::EINVAL, "invalid journal tag");
+                            };
+
+                            let data_log_block = self.wrap(log_block + 1 + i as u32);
+                            self.read_log_block(block_device, data_log_block, &mut data)?;
+                            if flags.contains(TagFlags::ESCAPE) {
+                                data[..4].copy_from_slice(&JBD2_MAGIC.to_be_bytes());
+                            }
+                            block_device.write_bytes(target as usize * BLOCK_SIZE, &data)?;
+                        }
+                    }
+                    nr_visited += tags.len() as u32;
+                    log_block = self.wrap(log_block + 1 + tags.len() as u32);
+                }
+                COMMIT_BLOCK => {
+                    sequence = sequence.wrapping_add(1);
+                    log_block = self.wrap(log_block + 1);
+                }
+                REVOKE_BLOCK => {
+                    if let Pass::Revoke { revoked, .. } = &mut pass {
+                        let record_size = if self.is_64bit { 8 } else { 4 };
+                        let count = (read_be32(&block, HEADER_SIZE) as usize).min(BLOCK_SIZE);
+                        let mut offset = REVOKE_HEADER_SIZE;
+                        while offset + record_size <= count {
+                            let target = if self.is_64bit {
+                                u64::from_be_bytes(block[offset..offset + 8].try_into().unwrap())
+                            } else {
+                                read_be32(&block, offset) as u64
+                            };
+                            let revoked_sequence = revoked.entry(target).or_insert(sequence);
+                            if seq_before(*revoked_sequence, sequence) {
+                                *revoked_sequence = sequence;
+                            }
+                            offset += record_size;
+                        }
+                    }
+                    log_block = self.wrap(log_block + 1);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(sequence)
+    }
+
+    /// Parses the tags in a descriptor block.
+    ///
+    /// Returns the target block numbers and the flags.
+    fn parse_tags(&self, block: &[u8]) -> Vec<(u64, TagFlags)> {
+        let tag_size = self.tag_size();
+        let mut tags = Vec::new();
+        let mut offset = HEADER_SIZE;
+
+        while offset + tag_size <= BLOCK_SIZE {
+            let mut target = read_be32(block, offset) as u64;
+            if self.is_64bit {
+                target |= (read_be32(block, offset + 8) as u64) << 32;
+            }
+            let flags = TagFlags::from_bits_truncate(u16::from_be_bytes(
+                block[offset + 6..offset + 8].try_into().unwrap(),
+            ));
+            tags.push((target, flags));
+
+            offset += tag_size;
+            if !flags.contains(TagFlags::SAME_UUID) {
+                offset += UUID_SIZE;
+            }
+            if flags.contains(TagFlags::LAST_TAG) {
+                break;
+            }
+        }
+
+        tags
+    }
+
+    /// Holds the metadata blocks in the running transaction.
+    ///
+    /// The blocks will be written in place when the transaction is committed.
+    pub fn add_blocks(&self, bid: Ext2Bid, bio_segment: &BioSegment) -> Result<()> {
+        let mut inner = self.inner.lock();
+        for i in 0..bio_segment.nblocks() {
+            let mut block = vec![0u8; BLOCK_SIZE].into_boxed_slice();
+            bio_segment.read_bytes(i * BLOCK_SIZE, &mut block)?;
+            inner.pending_blocks.insert(bid + i as Ext2Bid, block);
+        }
+        Ok(())
+    }
+
+    /// Holds the bytes of metadata blocks in the running transaction.
+    ///
+    /// The rest of the blocks are read from the running transaction, or from the device
+    /// if the blocks are not in the running transaction.
+    pub fn add_bytes(
+        &self,
+        block_device: &dyn BlockDevice,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let mut written = 0;
+        while written < buf.len() {
+            let bid = ((offset + written) / BLOCK_SIZE) as Ext2Bid;
+            let offset_in_block = (offset + written) % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - offset_in_block).min(buf.len() - written);
+
+            if !inner.pending_blocks.contains_key(&bid) {
+                let mut block = vec![0u8; BLOCK_SIZE].into_boxed_slice();
+                if len < BLOCK_SIZE {
+                    block_device.read_bytes(bid as usize * BLOCK_SIZE, &mut block)?;
+                }
+                inner.pending_blocks.insert(bid, block);
+            }
+            let block = inner.pending_blocks.get_mut(&bid).unwrap();
+            block[offset_in_block..offset_in_block + len]
+                .copy_from_slice(&buf[written..written + len]);
+
+            written += len;
+        }
+        Ok(())
+    }
+
+    /// Returns whether any of the blocks is held in the running transaction.
+    pub fn contains_blocks(&self, range: Range<Ext2Bid>) -> bool {
+        self.inner
+            .lock()
+            .pending_blocks
+            .range(range)
+            .next()
+            .is_some()
+    }
+
+    /// Copies the blocks held in the running transaction to the segment that has been read
+    /// from the device, so that the latest contents are seen.
+    pub fn copy_blocks_to(&self, bid: Ext2Bid, bio_segment: &BioSegment) -> Result<()> {
+        let inner = self.inner.lock();
+        let range = bid..bid + bio_segment.nblocks() as Ext2Bid;
+        for (pending_bid, block) in inner.pending_blocks.range(range) {
+            bio_segment.write_bytes((pending_bid - bid) as usize * BLOCK_SIZE, block)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the freed blocks from the running transaction.
+    ///
+    /// The freed blocks may be reused as data blocks, which must not be overwritten by
+    /// their stale contents as metadata blocks.
+    pub fn forget_blocks(&self, range: Range<Ext2Bid>) {
+        let mut inner = self.inner.lock();
+        let bids: Vec<Ext2Bid> = inner
+            .pending_blocks
+            .range(range)
+            .map(|(bid, _)| *bid)
+            .collect();
+        for bid in bids {
+            inner.pending_blocks.remove(&bid);
+        }
+    }
+
+    /// Commits the running transaction and writes the blocks in place.
+    ///
+    /// `set_needs_recovery` is called to set the flag in the superblock of the filesystem
+    /// before the blocks are written in place, and to clear the flag afterwards. Otherwise,
+    /// Linux would discard the log instead of replaying it.
+    pub fn commit(
+        &self,
+        block_device: &dyn BlockDevice,
+        set_needs_recovery: impl Fn(bool) -> Result<()>,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.pending_blocks.is_empty() {
+            return Ok(());
+        }
+
+        // The data blocks must reach the device before the metadata blocks referring to them.
+        flush(block_device)?;
+
+        let pending_blocks = core::mem::take(&mut inner.pending_blocks);
+        let pending_blocks: Vec<(Ext2Bid, Box<[u8]>)> = pending_blocks.into_iter().collect();
+        for transaction in pending_blocks.chunks(self.max_transaction_blocks()) {
+            self.write_transaction(block_device, &mut inner, transaction)?;
+
+            set_needs_recovery(true)?;
+            inner.start = self.first;
+            self.write_super_block(block_device, &mut inner)?;
+            flush(block_device)?;
+
+            // Checkpoint the transaction.
+            let mut bio_waiter = BioWaiter::new();
+            for (bid, block) in transaction {
+                bio_waiter
+                    .concat(block_device.write_bytes_async(*bid as usize * BLOCK_SIZE, block)?);
+            }
+            bio_waiter.wait().ok_or_else(|| {
+                Error::with_message(Errno::EIO, "failed to checkpoint the journal")
+            })?;
+            flush(block_device)?;
+
+            inner.start = 0;
+            inner.sequence = inner.sequence.wrapping_add(1);
+            self.write_super_block(block_device, &mut inner)?;
+            set_needs_recovery(false)?;
+            flush(block_device)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the blocks of a transaction to the log, followed by the commit block.
+    fn write_transaction(
+        &self,
+        block_device: &dyn BlockDevice,
+        inner: &mut Inner,
+        transaction: &[(Ext2Bid, Box<[u8]>)],
+    ) -> Result<()> {
+        let mut bio_waiter = BioWaiter::new();
+        let mut log_block = self.first;
+
+        for descriptor_blocks in transaction.chunks(self.tags_per_descriptor()) {
+            let mut descriptor = vec![0u8; BLOCK_SIZE];
+            write_header(&mut descriptor, DESCRIPTOR_BLOCK, inner.sequence);
+
+            let tag_size = self.tag_size();
+            let mut offset = HEADER_SIZE;
+            let descriptor_log_block = log_block;
+            log_block += 1;
+
+            for (i, (bid, block)) in descriptor_blocks.iter().enumerate() {
+                let mut flags = TagFlags::empty();
+                if i != 0 {
+                    flags |= TagFlags::SAME_UUID;
+                }
+                if i == descriptor_blocks.len() - 1 {
+                    flags |= TagFlags::LAST_TAG;
+                }
+
+                let mut log_data = block.clone();
+                if read_be32(block, 0) == JBD2_MAGIC {
+                    flags |= TagFlags::ESCAPE;
+                    log_data[..4].fill(0);
+                }
+                bio_waiter.concat(self.write_log_block_async(
+                    block_device,
+                    log_block,
+                    &log_data,
+                )?);
+                log_block += 1;
+
+                descriptor[offset..offset + 4].copy_from_slice(&bid.to_be_bytes());
+                descriptor[offset + 6..offset + 8].copy_from_slice(&flags.bits().to_be_bytes());
+                offset += tag_size;
+                if i == 0 {
+                    descriptor[offset..offset + UUID_SIZE].copy_from_slice(&self.uuid);
+                    offset += UUID_SIZE;
+                }
+            }
+
+            bio_waiter.concat(self.write_log_block_async(
+                block_device,
+                descriptor_log_block,
+                &descriptor,
+            )?);
+        }
+
+        bio_waiter
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to write the journal"))?;
+        flush(block_device)?;
+
+        let mut commit = vec![0u8; BLOCK_SIZE];
+        write_header(&mut commit, COMMIT_BLOCK, inner.sequence);
+        self.write_log_block_async(block_device, log_block, &commit)?
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to write the journal"))?;
+        flush(block_device)
+    }
+
+    /// Returns the maximum number of blocks in a transaction.
+    ///
+    /// A transaction must fit in the log together with its descriptor blocks and commit block.
+    fn max_transaction_blocks(&self) -> usize {
+        let log_len = (self.max_len - self.first) as usize;
+        let tags_per_descriptor = self.tags_per_descriptor();
+        (log_len - 2) * tags_per_descriptor / (tags_per_descriptor + 1)
+    }
+
+    /// Returns the number of tags in a descriptor block.
+    fn tags_per_descriptor(&self) -> usize {
+        (BLOCK_SIZE - HEADER_SIZE - UUID_SIZE) / self.tag_size()
+    }
+
+    fn tag_size(&self) -> usize {
+        if self.is_64bit {
+            12
+        } else {
+            8
+        }
+    }
+
+    fn write_super_block(&self, block_device: &dyn BlockDevice, inner: &mut Inner) -> Result<()> {
+        let sequence = inner.sequence;
+        let start = inner.start;
+        inner.super_block[SEQUENCE_OFFSET..SEQUENCE_OFFSET + 4]
+            .copy_from_slice(&sequence.to_be_bytes());
+        inner.super_block[START_OFFSET..START_OFFSET + 4].copy_from_slice(&start.to_be_bytes());
+        block_device.write_bytes(self.blocks[0] as usize * BLOCK_SIZE, &inner.super_block)?;
+        Ok(())
+    }
+
+    fn read_log_block(
+        &self,
+        block_device: &dyn BlockDevice,
+        log_block: u32,
+        buf: &mut [u8],
+    ) -> Result<()> {
+        let bid = self.blocks[log_block as usize];
+        block_device.read_bytes(bid as usize * BLOCK_SIZE, buf)?;
+        Ok(())
+    }
+
+    fn write_log_block_async(
+        &self,
+        block_device: &dyn BlockDevice,
+        log_block: u32,
+        buf: &[u8],
+    ) -> Result<BioWaiter> {
+        let bid = self.blocks[log_block as usize];
+        let waiter = block_device.write_bytes_async(bid as usize * BLOCK_SIZE, buf)?;
+        Ok(waiter)
+    }
+
+    /// Wraps the index of a log block around the end of the log.
+    fn wrap(&self, log_block: u32) -> u32 {
+        if log_block >= self.max_len {
+            log_block - (self.max_len - self.first)
+        } else {
+            log_block
+        }
+    }
+}
+
+impl Debug for Journal {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Journal")
+            .field("first", &self.first)
+            .field("max_len", &self.max_len)
+            .field("is_64bit", &self.is_64bit)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The passes of the recovery.
+enum Pass<'a> {
+    /// Finds the end of the committed transactions.
+    Scan,
+    /// Collects the revoked blocks and the latest transactions that revoke them.
+    Revoke {
+        end_sequence: u32,
+        revoked: &'a mut BTreeMap<u64, u32>,
+    },
+    /// Writes the blocks that are not revoked in place.
+    Replay {
+        end_sequence: u32,
+        revoked: &'a BTreeMap<u64, u32>,
+    },
+}
+
+fn write_header(block: &mut [u8], block_type: u32, sequence: u32) {
+    block[0x0..0x4].copy_from_slice(&JBD2_MAGIC.to_be_bytes());
+    block[0x4..0x8].copy_from_slice(&block_type.to_be_bytes());
+    block[0x8..0xC].copy_from_slice(&sequence.to_be_bytes());
+}
+
+fn read_be32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns whether the sequence number `a` is before `b`, considering the wrap-around.
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+fn flush(block_device: &dyn BlockDevice) -> Result<()> {
+    match block_device.sync()? {
+        BioStatus::Complete => Ok(()),
+        err_status => Err(Error::from(err_status)),
+    }
+}
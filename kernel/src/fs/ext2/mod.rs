@@ -14,6 +14,9 @@
 //!    stored in PageCache, which accelerates the performance of data access.
 //! 3. Compatible with queue-based block device. The filesystem can submits multiple
 //!    BIO requests to be block device at once, thereby enhancing I/O performance.
+//! 4. Compatible with Ext3 and Ext4. The extents, the hash-indexed directories, the 64-bit
+//!    group descriptors, and the extended attributes in inodes are supported. The metadata
+//!    are journaled in the ordered mode, so the filesystem is consistent after a crash.
 //!
 //! # Example
 //!
@@ -35,19 +38,29 @@
 //! Here we summarizes the features that need to be implemented in the future.
 //! 1. Supports merging small read/write operations.
 //! 2. Handles the intermediate failure status correctly.
+//! 3. Supports the metadata checksums.
+//! 4. Supports the block IDs beyond 32 bits.
+//! 5. Supports writing to holes and uninitialized extents.
+//! 6. Maintains the hash index of directories on modification. Now the index is dropped
+//!    once the directory is modified.
 
 pub use fs::Ext2;
-pub use inode::{FilePerm, Inode};
+pub use inode::{FileFlags, FilePerm, Inode};
 pub use super_block::{SuperBlock, MAGIC_NUM};
 
 mod block_group;
 mod block_ptr;
 mod dir;
+mod extent;
 mod fs;
+mod htree;
 mod impl_for_vfs;
 mod indirect_block_cache;
 mod inode;
+mod journal;
 mod prelude;
 mod super_block;
+#[cfg(ktest)]
+mod test;
 mod utils;
 mod xattr;
@@ -93,13 +93,19 @@ pub struct SuperBlock {
     prealloc_file_blocks: u8,
     /// Number of blocks to preallocate for directories.
     prealloc_dir_blocks: u8,
+    /// Size of group descriptors.
+    desc_size: usize,
+    /// The raw superblock loaded from the device.
+    ///
+    /// The fields that are not tracked above are written back as is.
+    raw: RawSuperBlock,
 }
 
 impl TryFrom<RawSuperBlock> for SuperBlock {
     type Error = crate::error::Error;
 
     fn try_from(sb: RawSuperBlock) -> Result<Self> {
-        Ok(Self {
+        let super_block = Self {
             inodes_count: sb.inodes_count,
             blocks_count: sb.blocks_count,
             reserved_blocks_count: sb.reserved_blocks_count,
@@ -174,11 +180,51 @@ impl TryFrom<RawSuperBlock> for SuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
-        })
+            desc_size: if FeatureInCompatSet::from_bits_truncate(sb.feature_incompat)
+                .contains(FeatureInCompatSet::BIT64)
+            {
+                sb.desc_size as usize
+            } else {
+                GROUP_DESC_SIZE
+            },
+            raw: sb,
+        };
+        super_block.check_features()?;
+        Ok(super_block)
     }
 }
 
 impl SuperBlock {
+    /// Checks whether the features and the layout of the filesystem are supported.
+    fn check_features(&self) -> Result<()> {
+        if self
+            .feature_incompat
+            .intersects(FeatureInCompatSet::UNSUPPORTED)
+        {
+            return_errno_with_message!(Errno::EINVAL, "unsupported incompatible features");
+        }
+        if self
+            .feature_ro_compat
+            .intersects(FeatureRoCompatSet::UNSUPPORTED)
+        {
+            return_errno_with_message!(Errno::EINVAL, "unsupported read-only compatible features");
+        }
+
+        if self.desc_size < GROUP_DESC_SIZE
+            || !self.desc_size.is_power_of_two()
+            || self.desc_size > self.block_size
+        {
+            return_errno_with_message!(Errno::EINVAL, "invalid group descriptor size");
+        }
+        // The block IDs are 32-bit. So the 64-bit feature is supported only if all the blocks
+        // can be addressed by 32-bit block IDs.
+        if self.raw.blocks_count_hi != 0 {
+            return_errno_with_message!(Errno::EINVAL, "too many blocks");
+        }
+
+        Ok(())
+    }
+
     /// Returns the block size.
     pub fn block_size(&self) -> usize {
         self.block_size
@@ -216,7 +262,42 @@ impl SuperBlock {
 
     /// Returns the number of block groups.
     pub fn block_groups_count(&self) -> u32 {
-        self.blocks_count / self.blocks_per_group
+        // The last block group may be smaller than the others.
+        (self.blocks_count - self.first_data_block.to_raw() as u32).div_ceil(self.blocks_per_group)
+    }
+
+    /// Returns the size of group descriptors.
+    pub fn desc_size(&self) -> usize {
+        self.desc_size
+    }
+
+    /// Returns the number of blocks reserved for growing the group descriptor table.
+    pub fn reserved_gdt_blocks(&self) -> u32 {
+        self.raw.reserved_gdt_blocks as u32
+    }
+
+    /// Returns the inode number of the journal, if the filesystem has a journal.
+    pub fn journal_ino(&self) -> Option<u32> {
+        if self.feature_compat.contains(FeatureCompatSet::HAS_JOURNAL) {
+            Some(self.raw.journal_ino)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the seed of the directory hash.
+    pub fn hash_seed(&self) -> [u32; 4] {
+        self.raw.hash_seed
+    }
+
+    /// Returns whether the directory hash treats the characters as unsigned.
+    pub fn is_hash_unsigned(&self) -> bool {
+        self.raw.flags & SB_FLAG_UNSIGNED_HASH != 0
+    }
+
+    /// Returns the size of the extra fields of new inodes.
+    pub fn want_extra_isize(&self) -> usize {
+        self.raw.want_extra_isize as usize
     }
 
     /// Returns the filesystem state.
@@ -249,6 +330,16 @@ impl SuperBlock {
         self.free_blocks_count
     }
 
+    /// Returns the number of blocks reserved for the super user.
+    pub fn reserved_blocks_count(&self) -> u32 {
+        self.reserved_blocks_count
+    }
+
+    /// Returns the 128-bit UUID of the volume.
+    pub fn uuid(&self) -> [u8; 16] {
+        self.uuid
+    }
+
     /// Increase the number of free blocks.
     pub(super) fn inc_free_blocks(&mut self, count: u32) {
         self.free_blocks_count = self.free_blocks_count.checked_add(count).unwrap();
@@ -311,6 +402,28 @@ impl SuperBlock {
         Bid::new(super_block_bid as u64)
     }
 
+    /// Returns the number of blocks of the group descriptor table.
+    pub(super) fn group_descriptors_blocks(&self) -> usize {
+        (self.block_groups_count() as usize * self.desc_size).div_ceil(self.block_size)
+    }
+
+    /// Returns whether the block group contains the super block or its backup.
+    pub(super) fn has_super_block(&self, block_group_idx: usize) -> bool {
+        block_group_idx == 0 || self.is_backup_group(block_group_idx)
+    }
+
+    /// Returns whether the group descriptors have checksums.
+    pub(super) fn has_group_descriptor_checksum(&self) -> bool {
+        self.feature_ro_compat
+            .contains(FeatureRoCompatSet::GDT_CSUM)
+    }
+
+    /// Sets or clears the flag that indicates the journal needs to be replayed.
+    pub(super) fn set_needs_recovery(&mut self, needs_recovery: bool) {
+        self.feature_incompat
+            .set(FeatureInCompatSet::RECOVER, needs_recovery);
+    }
+
     /// Returns the starting block id of the block group descriptor table
     /// inside the block group pointed by `block_group_idx`.
     ///
@@ -339,6 +452,20 @@ bitflags! {
         const RESIZE_INO = 1 << 4;
         /// Directories use hash index
         const DIR_INDEX = 1 << 5;
+        /// Block groups are lazily initialized (obsolete)
+        const LAZY_BG = 1 << 6;
+        /// Exclude inode (not used)
+        const EXCLUDE_INODE = 1 << 7;
+        /// Exclude bitmap (not used)
+        const EXCLUDE_BITMAP = 1 << 8;
+        /// Only two backup superblocks exist
+        const SPARSE_SUPER2 = 1 << 9;
+        /// File system has fast commits in the journal
+        const FAST_COMMIT = 1 << 10;
+        /// Inode numbers are stable across resizes
+        const STABLE_INODES = 1 << 11;
+        /// File system has an orphan file
+        const ORPHAN_FILE = 1 << 12;
     }
 }
 
@@ -355,6 +482,41 @@ bitflags! {
         const JOURNAL_DEV = 1 << 3;
         /// Metablock block group
         const META_BG = 1 << 4;
+        /// Files use extents
+        const EXTENTS = 1 << 6;
+        /// File system can have more than 2^32 blocks
+        const BIT64 = 1 << 7;
+        /// Multiple mount protection
+        const MMP = 1 << 8;
+        /// Flexible block groups
+        const FLEX_BG = 1 << 9;
+        /// Inodes can be used to store large extended attribute values
+        const EA_INODE = 1 << 10;
+        /// Data in directory entries
+        const DIRDATA = 1 << 12;
+        /// Metadata checksum seed is stored in the superblock
+        const CSUM_SEED = 1 << 13;
+        /// Large directories (> 2GB) or three-level hash trees
+        const LARGEDIR = 1 << 14;
+        /// Data in inodes
+        const INLINE_DATA = 1 << 15;
+        /// Encrypted inodes
+        const ENCRYPT = 1 << 16;
+        /// Case-insensitive directories
+        const CASEFOLD = 1 << 17;
+
+        /// The features that are not supported.
+        const UNSUPPORTED = Self::COMPRESSION.bits
+            | Self::JOURNAL_DEV.bits
+            | Self::META_BG.bits
+            | Self::MMP.bits
+            | Self::EA_INODE.bits
+            | Self::DIRDATA.bits
+            | Self::CSUM_SEED.bits
+            | Self::LARGEDIR.bits
+            | Self::INLINE_DATA.bits
+            | Self::ENCRYPT.bits
+            | Self::CASEFOLD.bits;
     }
 }
 
@@ -367,6 +529,47 @@ bitflags! {
         const LARGE_FILE = 1 << 1;
         /// Directory contents are stored in the form of a Binary Tree
         const BTREE_DIR = 1 << 2;
+        /// File sizes can be larger than 2TB
+        const HUGE_FILE = 1 << 3;
+        /// Group descriptors have checksums
+        const GDT_CSUM = 1 << 4;
+        /// Directories can have more than 65000 subdirectories
+        const DIR_NLINK = 1 << 5;
+        /// Inodes have extra fields
+        const EXTRA_ISIZE = 1 << 6;
+        /// File system has a snapshot
+        const HAS_SNAPSHOT = 1 << 7;
+        /// Quotas
+        const QUOTA = 1 << 8;
+        /// Blocks are allocated in clusters
+        const BIGALLOC = 1 << 9;
+        /// Metadata has checksums
+        const METADATA_CSUM = 1 << 10;
+        /// Replicas
+        const REPLICA = 1 << 11;
+        /// Read-only file system image
+        const READONLY = 1 << 12;
+        /// Project quotas
+        const PROJECT = 1 << 13;
+        /// Blocks can be shared
+        const SHARED_BLOCKS = 1 << 14;
+        /// Verity inodes
+        const VERITY = 1 << 15;
+        /// Orphan file has entries
+        const ORPHAN_PRESENT = 1 << 16;
+
+        /// The features that are not supported.
+        const UNSUPPORTED = Self::BTREE_DIR.bits
+            | Self::HAS_SNAPSHOT.bits
+            | Self::QUOTA.bits
+            | Self::BIGALLOC.bits
+            | Self::METADATA_CSUM.bits
+            | Self::REPLICA.bits
+            | Self::READONLY.bits
+            | Self::PROJECT.bits
+            | Self::SHARED_BLOCKS.bits
+            | Self::VERITY.bits
+            | Self::ORPHAN_PRESENT.bits;
     }
 }
 
@@ -417,6 +620,12 @@ pub enum RevLevel {
     Dynamic = 1,
 }
 
+/// The size of group descriptors if the 64-bit feature is disabled.
+pub(super) const GROUP_DESC_SIZE: usize = 32;
+
+/// The superblock flag that indicates the directory hash treats the characters as unsigned.
+const SB_FLAG_UNSIGNED_HASH: u32 = 0x2;
+
 const_assert!(core::mem::size_of::<RawSuperBlock>() == SUPER_BLOCK_SIZE);
 
 /// The raw superblock, it must be exactly 1024 bytes in length.
@@ -465,7 +674,8 @@ pub(super) struct RawSuperBlock {
     pub algorithm_usage_bitmap: u32,
     pub prealloc_file_blocks: u8,
     pub prealloc_dir_blocks: u8,
-    padding1: u16,
+    /// Number of blocks reserved for growing the group descriptor table.
+    pub reserved_gdt_blocks: u16,
     ///
     /// This fields are for journaling support in Ext3.
     ///
@@ -481,12 +691,29 @@ pub(super) struct RawSuperBlock {
     pub hash_seed: [u32; 4],
     /// Default hash version to use
     pub def_hash_version: u8,
-    reserved_char_pad: u8,
-    reserved_word_pad: u16,
+    pub journal_backup_type: u8,
+    /// Size of group descriptors if the 64-bit feature is enabled.
+    pub desc_size: u16,
     /// Default mount options.
     pub default_mount_opts: u32,
     /// First metablock block group.
     pub first_meta_bg: u32,
+    ///
+    /// This fields are for Ext4.
+    ///
+    /// Time when the filesystem was created.
+    pub mkfs_time: UnixTime,
+    /// Backup of the block pointers of the journal inode.
+    pub journal_blocks: [u32; 17],
+    pub blocks_count_hi: u32,
+    pub reserved_blocks_count_hi: u32,
+    pub free_blocks_count_hi: u32,
+    /// All inodes have at least this many extra bytes.
+    pub min_extra_isize: u16,
+    /// New inodes should reserve this many extra bytes.
+    pub want_extra_isize: u16,
+    /// Miscellaneous flags.
+    pub flags: u32,
     reserved: Reserved,
 }
 
@@ -528,17 +755,17 @@ impl From<&SuperBlock> for RawSuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
-            ..Default::default()
+            ..sb.raw
         }
     }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod)]
-struct Reserved([u32; 190]);
+struct Reserved([u32; 167]);
 
 impl Default for Reserved {
     fn default() -> Self {
-        Self([0u32; 190])
+        Self([0u32; 167])
     }
 }
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The tests against the Ext4 image created by `tools/mkfs_ext4_fixture.sh`.
+
+use alloc::format;
+
+use aster_block::BLOCK_SIZE;
+use ostd::prelude::ktest;
+
+use super::{Ext2, FileFlags, Inode};
+use crate::{fs::utils::MemoryDisk, prelude::*};
+
+/// The Ext4 image.
+static EXT4_IMAGE: &[u8] = include_bytes!("../../../../test/build/ext4.img");
+
+/// The number of the blocks of `/extents`.
+const EXTENTS_BLOCKS: usize = 24;
+/// The number of the files in `/htree`.
+const HTREE_FILES: usize = 500;
+
+fn load_ext4() -> Arc<Ext2> {
+    Ext2::open(MemoryDisk::from_image(EXT4_IMAGE)).unwrap()
+}
+
+fn lookup(fs: &Ext2, name: &str) -> Arc<Inode> {
+    fs.root_inode().unwrap().lookup(name).unwrap()
+}
+
+fn read_all(inode: &Inode) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; inode.file_size()];
+    let len = inode.read_at(0, &mut VmWriter::from(buf.as_mut_slice()).to_fallible())?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[ktest]
+fn extent_tree() {
+    let fs = load_ext4();
+    let file = lookup(&fs, "extents");
+    let data = read_all(&file).unwrap();
+    assert_eq!(data.len(), EXTENTS_BLOCKS * BLOCK_SIZE);
+
+    // The even blocks before the 20th block are filled with the block index plus one. The
+    // other blocks are holes.
+    for (idx, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        let expected = if idx % 2 == 0 && idx < 20 {
+            idx as u8 + 1
+        } else {
+            0
+        };
+        assert!(
+            block.iter().all(|byte| *byte == expected),
+            "block {} is not filled with {}",
+            idx,
+            expected
+        );
+    }
+}
+
+#[ktest]
+fn extent_tree_bad_depth() {
+    let fs = load_ext4();
+    let file = lookup(&fs, "bad_depth");
+    assert_eq!(read_all(&file).unwrap_err().error(), Errno::EIO);
+}
+
+#[ktest]
+fn htree_lookup() {
+    let fs = load_ext4();
+    let dir = lookup(&fs, "htree");
+    assert!(dir.file_flags().contains(FileFlags::INDEX_DIR));
+
+    for idx in [0, 1, HTREE_FILES / 2, HTREE_FILES - 1] {
+        let name = format!("file_{:03}", idx);
+        assert!(dir.lookup(&name).is_ok(), "{} is not found", name);
+    }
+    assert_eq!(dir.lookup("file_500").unwrap_err().error(), Errno::ENOENT);
+}
+
+#[ktest]
+fn dir_entries() {
+    let fs = load_ext4();
+    let dir = lookup(&fs, "htree");
+    let mut names: Vec<String> = Vec::new();
+    dir.readdir_at(0, &mut names).unwrap();
+
+    // The index nodes are hidden as empty entries.
+    assert_eq!(names.len(), HTREE_FILES + 2);
+    names.sort();
+    assert_eq!(names[0], ".");
+    assert_eq!(names[1], "..");
+    for (idx, name) in names[2..].iter().enumerate() {
+        assert_eq!(*name, format!("file_{:03}", idx));
+    }
+}
+
+#[ktest]
+fn dir_entries_bad_rec_len() {
+    let fs = load_ext4();
+    let dir = lookup(&fs, "bad_rec_len");
+    let mut names: Vec<String> = Vec::new();
+    assert_eq!(
+        dir.readdir_at(0, &mut names).unwrap_err().error(),
+        Errno::EIO
+    );
+    assert!(names.is_empty());
+}
@@ -1,9 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::mm::UntypedMem;
-
-use super::{block_ptr::Ext2Bid, prelude::*, Ext2, Inode};
-use crate::fs::utils::{XattrName, XattrNamespace, XattrSetFlags, XATTR_NAME_MAX_LEN};
+use super::{block_ptr::Ext2Bid, inode::RawInode, prelude::*, Ext2, Inode};
+use crate::fs::utils::{XattrName, XattrNamespace, XattrSetFlags};
 
 const EXT2_XATTR_MAGIC: u32 = 0xEA020000;
 
@@ -16,12 +14,16 @@ struct XattrHeader {
     ref_count: u32,
     nblocks: u32,
     hash: u32,
-    reserved: [u32; 4],
+    checksum: u32,
+    reserved: [u32; 3],
 }
 
 const XATTR_HEADER_SIZE: usize = size_of::<XattrHeader>();
+/// The size of the header of the in-inode xattrs, which contains only the magic number.
+const XATTR_IBODY_HEADER_SIZE: usize = size_of::<u32>();
 const XATTR_ALIGN: usize = 4;
-const XATTR_ENTRY_VALUE_GAP: usize = XATTR_ALIGN;
+/// The size of the null bytes that terminate the entries.
+const XATTR_ENTRY_END_SIZE: usize = XATTR_ALIGN;
 
 /// The xattr entry of an ext2 inode, organized on an xattr block.
 #[repr(C)]
@@ -30,16 +32,21 @@ struct XattrEntry {
     name_len: u8,
     name_index: u8,
     value_offset: u16,
-    value_block: u32,
+    value_inum: u32,
     value_len: u32,
     hash: u32,
 }
 
 const XATTR_ENTRY_SIZE: usize = size_of::<XattrEntry>();
 
-/// An xattr object of an ext2 inode.
+/// The xattrs of an ext2 inode.
 /// An xattr is used to manage special 'name-value' pairs of an inode.
 ///
+/// The xattrs are stored in two regions. One is the space following the extra fields in
+/// the inode (the in-inode xattrs), which exists only if the inode is larger than 128 bytes.
+/// The other is an xattr block, which may be shared by the inodes with the same xattrs.
+/// New xattrs are stored in the inode first if there is enough room.
+///
 /// An xattr block layout (objects are aligned to 4 bytes):
 ///  +--------------------+
 ///  | XattrHeader        |
@@ -56,26 +63,54 @@ const XATTR_ENTRY_SIZE: usize = size_of::<XattrEntry>();
 ///  | XattrValue 2       | | growing upwards
 ///  | XattrValue 1       | |
 ///  +--------------------+
+///
+/// The in-inode xattrs have the same layout, except that the header contains only the
+/// magic number, and the value offsets are relative to the first entry.
 #[derive(Debug)]
 pub(super) struct Xattr {
-    /// The buffer of the xattr block, which always keeps its content up-to-date.
-    blocks_buf: USegment,
-    /// A cache to assist the xattr operations.
-    cache: RwMutex<Dirty<XattrCache>>,
+    inner: RwMutex<XattrInner>,
     inode: Weak<Inode>,
     fs: Weak<Ext2>,
 }
 
-/// Gathers helper metadata to describe an xattr block. This is
-/// primarily for convenience when manipulating xattr entries or values.
-/// Without this cache, the raw buffer would need to be parsed every time.
 #[derive(Debug)]
-struct XattrCache {
-    bid: Bid,
-    header: Option<XattrHeader>,
-    entries: BTreeMap<usize, XattrEntry>, // K: offset, V: entry
-    values: BTreeMap<usize, usize>,       // K: offset, V: len
-    capacity_bytes: usize,
+struct XattrInner {
+    /// The in-inode xattrs, or `None` if the inode has no room for them.
+    ibody: Option<XattrRegion>,
+    /// The xattrs in the xattr block.
+    block: XattrRegion,
+    /// Whether the regions are loaded from the device.
+    ///
+    /// The regions are loaded lazily when actual xattr operations are performed.
+    is_loaded: bool,
+}
+
+/// A region that stores xattrs.
+#[derive(Debug)]
+struct XattrRegion {
+    location: XattrLocation,
+    /// The size of the region in bytes.
+    capacity: usize,
+    /// The reference count of the xattr block.
+    ref_count: u32,
+    items: Vec<XattrItem>,
+    is_dirty: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum XattrLocation {
+    /// The xattr block. The block ID is zero if the block is not allocated.
+    Block(Ext2Bid),
+    /// The space in the inode starting from `offset` after the 128-byte raw inode.
+    Inode { offset: usize },
+}
+
+/// An xattr, whose name does not contain the prefix of the namespace.
+#[derive(Clone, Debug)]
+struct XattrItem {
+    name_index: u8,
+    name: Vec<u8>,
+    value: Vec<u8>,
 }
 
 /// The number of blocks for an xattr. This value should be
@@ -84,7 +119,7 @@ pub(super) const XATTR_NBLOCKS: usize = 1;
 
 impl XattrEntry {
     fn total_len(&self) -> usize {
-        XATTR_ENTRY_SIZE + (self.name_len as usize).align_up(XATTR_ALIGN)
+        Self::target_len(self.name_len as usize)
     }
 
     fn target_len(name_len: usize) -> usize {
@@ -94,58 +129,65 @@ impl XattrEntry {
 
 impl Xattr {
     pub fn new(bid: Bid, inode: Weak<Inode>, fs: Weak<Ext2>) -> Self {
-        let blocks_buf: USegment = FrameAllocOptions::new()
-            .zeroed(true)
-            .alloc_segment(XATTR_NBLOCKS)
-            .unwrap()
-            .into();
+        let block = XattrRegion::new(XattrLocation::Block(bid.to_raw() as Ext2Bid), BLOCK_SIZE);
         Self {
-            cache: RwMutex::new(Dirty::new(XattrCache::new(bid, blocks_buf.size()))),
-            blocks_buf,
+            inner: RwMutex::new(XattrInner {
+                ibody: None,
+                block,
+                is_loaded: false,
+            }),
             inode,
             fs,
         }
     }
 
-    /// Lazily initialize the xattr structures only when actual xattr operations are performed.
-    fn lazy_init(&self) -> Result<()> {
-        let fs = self.fs();
-        let cache = self.cache.upread();
+    /// Lazily loads the xattrs only when actual xattr operations are performed.
+    fn load(&self) -> Result<()> {
+        let inner = self.inner.upread();
+        if inner.is_loaded {
+            return Ok(());
+        }
 
-        // Need to allocate a new xattr block
-        if cache.bid.to_raw() == 0 {
-            assert!(cache.header.is_none());
-            let new_bid = {
-                let allocated = fs
-                    .alloc_blocks(self.inode().block_group_idx(), XATTR_NBLOCKS as _)
-                    .ok_or(Error::new(Errno::ENOSPC))?
-                    .start as u64;
-                Bid::new(allocated)
-            };
+        let fs = self.fs();
+        let ino = self.inode().ino();
+        let mut inner = inner.upgrade();
+
+        // Load the in-inode xattrs, which follow the extra fields of the inode.
+        let inode_extra_size = fs.inode_size() - size_of::<RawInode>();
+        if inode_extra_size > 0 {
+            let mut extra_isize = [0u8; 2];
+            fs.read_inode_extra(ino, 0, &mut extra_isize)?;
+            let offset = u16::from_le_bytes(extra_isize) as usize;
+            if offset % XATTR_ALIGN == 0 && offset + XATTR_IBODY_HEADER_SIZE < inode_extra_size {
+                let mut ibody =
+                    XattrRegion::new(XattrLocation::Inode { offset }, inode_extra_size - offset);
+                let mut buf = vec![0u8; ibody.capacity];
+                fs.read_inode_extra(ino, offset, &mut buf)?;
+                if u32::from_le_bytes(buf[..4].try_into().unwrap()) == EXT2_XATTR_MAGIC {
+                    ibody.items = ibody.parse(&buf)?;
+                }
+                inner.ibody = Some(ibody);
+            }
+        }
 
-            let new_header = XattrHeader::default();
-            self.blocks_buf.write_val(0, &new_header)?;
-
-            let mut cache = cache.upgrade();
-            cache.header = Some(new_header);
-            cache.bid = new_bid;
-            self.inode().set_acl(new_bid);
-        // Need to load the xattr block from device
-        } else if cache.header.is_none() {
-            fs.block_device().read_blocks(
-                cache.bid,
-                BioSegment::new_from_segment(self.blocks_buf.clone(), BioDirection::FromDevice),
-            )?;
-
-            let header = self.blocks_buf.read_val::<XattrHeader>(0)?;
-            if header.magic != EXT2_XATTR_MAGIC {
+        // Load the xattr block.
+        if let XattrLocation::Block(bid) = inner.block.location
+            && bid != 0
+        {
+            let bio_segment = BioSegment::alloc(XATTR_NBLOCKS, BioDirection::FromDevice);
+            fs.read_blocks(bid, bio_segment.clone())?;
+            let mut buf = vec![0u8; BLOCK_SIZE];
+            bio_segment.read_bytes(0, &mut buf)?;
+
+            let header = XattrHeader::from_bytes(&buf[..XATTR_HEADER_SIZE]);
+            if header.magic != EXT2_XATTR_MAGIC || header.nblocks != XATTR_NBLOCKS as u32 {
                 return_errno_with_message!(Errno::EINVAL, "invalid xattr magic");
             }
-
-            let mut cache = cache.upgrade();
-            cache.header = Some(header);
-            cache.build_entries_from_buf(&self.blocks_buf)?;
+            inner.block.ref_count = header.ref_count;
+            inner.block.items = inner.block.parse(&buf)?;
         }
+
+        inner.is_loaded = true;
         Ok(())
     }
 
@@ -155,90 +197,68 @@ impl Xattr {
         value_reader: &mut VmReader,
         flags: XattrSetFlags,
     ) -> Result<()> {
-        self.lazy_init()?;
-
-        let namespace = name.namespace();
-        let name_len = name.full_name_len();
-        let value_len = value_reader.remain();
-        let cache = self.cache.upread();
-        if let Some((offset, mut entry)) = cache.find_entry(&name, &self.blocks_buf) {
-            if flags.contains(XattrSetFlags::CREATE_ONLY) {
-                return_errno_with_message!(Errno::EEXIST, "the target xattr already exists");
-            }
-
-            if value_len <= entry.value_len as usize {
-                self.blocks_buf
-                    .write(entry.value_offset as _, value_reader)?;
-
-                if value_len != entry.value_len as usize {
-                    entry.value_len = value_len as _;
-                    self.blocks_buf.write_val(offset, &entry)?;
+        self.load()?;
+
+        let (name_index, name_suffix) = name_to_raw(&name);
+        let mut value = vec![0u8; value_reader.remain()];
+        value_reader.read_fallible(&mut VmWriter::from(value.as_mut_slice()).to_fallible())?;
+        let new_item = XattrItem {
+            name_index,
+            name: name_suffix.to_vec(),
+            value,
+        };
+
+        let mut inner = self.inner.write();
+        let inner = &mut *inner;
+        let is_in_ibody = inner
+            .ibody
+            .as_ref()
+            .is_some_and(|ibody| ibody.find(name_index, name_suffix).is_some());
+        let is_in_block = inner.block.find(name_index, name_suffix).is_some();
+
+        if flags.contains(XattrSetFlags::CREATE_ONLY) && (is_in_ibody || is_in_block) {
+            return_errno_with_message!(Errno::EEXIST, "the target xattr already exists");
+        }
+        if flags.contains(XattrSetFlags::REPLACE_ONLY) && !is_in_ibody && !is_in_block {
+            return_errno_with_message!(Errno::ENODATA, "the target xattr does not exist");
+        }
 
-                    let mut cache = cache.upgrade();
-                    let _ = cache.entries.insert(offset, entry);
-                    let _ = cache.values.insert(entry.value_offset as _, value_len);
-                }
-            } else {
-                let new_value_offset = cache
-                    .find_room_for_new_value(value_len)
-                    .ok_or(Error::new(Errno::ENOSPC))?;
-                self.blocks_buf.write(new_value_offset, value_reader)?;
-
-                let mut cache = cache.upgrade();
-                let _ = cache.values.remove(&(entry.value_offset as _));
-                entry.value_offset = new_value_offset as _;
-                entry.value_len = value_len as _;
-                self.blocks_buf.write_val(offset, &entry)?;
-
-                let _ = cache.entries.insert(offset, entry);
-                let _ = cache.values.insert(new_value_offset as _, value_len);
+        // Try to store the xattr in the same region, then in the inode, then in the block.
+        if is_in_block && inner.block.try_insert(new_item.clone()) {
+            return Ok(());
+        }
+        if let Some(ibody) = inner.ibody.as_mut()
+            && ibody.try_insert(new_item.clone())
+        {
+            if is_in_block {
+                inner.block.remove(name_index, name_suffix);
             }
-        } else {
-            if flags.contains(XattrSetFlags::REPLACE_ONLY) {
-                return_errno_with_message!(Errno::ENODATA, "the target xattr does not exist");
+            return Ok(());
+        }
+        if !is_in_block && inner.block.try_insert(new_item) {
+            if let Some(ibody) = inner.ibody.as_mut()
+                && is_in_ibody
+            {
+                ibody.remove(name_index, name_suffix);
             }
-
-            let new_entry_offset = cache
-                .find_room_for_new_entry(name_len)
-                .ok_or(Error::new(Errno::ENOSPC))?;
-            let new_value_offset = cache
-                .find_room_for_new_value(value_len)
-                .ok_or(Error::new(Errno::ENOSPC))?;
-            let new_entry = XattrEntry {
-                name_len: name_len as _,
-                name_index: namespace as _,
-                value_offset: new_value_offset as _,
-                value_block: 0, // TBD
-                value_len: value_len as _,
-                hash: 0, // TBD
-            };
-
-            self.blocks_buf.write_val(new_entry_offset, &new_entry)?;
-            self.blocks_buf.write_bytes(
-                new_entry_offset + XATTR_ENTRY_SIZE,
-                name.full_name().as_bytes(),
-            )?;
-            self.blocks_buf.write(new_value_offset, value_reader)?;
-
-            let mut cache = cache.upgrade();
-            let _ = cache.entries.insert(new_entry_offset, new_entry);
-            let _ = cache.values.insert(new_value_offset, value_len);
+            return Ok(());
         }
 
-        Ok(())
+        return_errno_with_message!(Errno::ENOSPC, "no space for the xattr");
     }
 
     pub fn get(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
-        self.lazy_init()?;
+        self.load()?;
 
-        let value_avail_len = value_writer.avail();
-        let (_, entry) = self
-            .cache
-            .read()
-            .find_entry(&name, &self.blocks_buf)
+        let (name_index, name_suffix) = name_to_raw(&name);
+        let inner = self.inner.read();
+        let item = inner
+            .regions()
+            .find_map(|region| region.find(name_index, name_suffix))
             .ok_or(Error::new(Errno::ENODATA))?;
 
-        let value_len = entry.value_len as usize;
+        let value_avail_len = value_writer.avail();
+        let value_len = item.value.len();
         if value_avail_len == 0 {
             return Ok(value_len);
         }
@@ -246,41 +266,33 @@ impl Xattr {
             return_errno_with_message!(Errno::ERANGE, "the xattr value buffer is too small");
         }
 
-        value_writer.write_fallible(
-            &mut self
-                .blocks_buf
-                .reader()
-                .to_fallible()
-                .skip(entry.value_offset as usize)
-                .limit(value_len),
-        )?;
-
+        value_writer.write_fallible(&mut VmReader::from(item.value.as_slice()).to_fallible())?;
         Ok(value_len)
     }
 
     pub fn list(&self, namespace: XattrNamespace, list_writer: &mut VmWriter) -> Result<usize> {
-        self.lazy_init()?;
+        self.load()?;
 
         let list_avail_len = list_writer.avail();
-        let cache = self.cache.read();
-
-        let target_list: Vec<_> = cache
-            .entries
-            .iter()
-            .filter_map(|(offset, entry)| {
-                if namespace.is_user() && entry.name_index != XattrNamespace::User as u8 {
+        let inner = self.inner.read();
+
+        let target_list: Vec<(XattrNamespace, &[u8])> = inner
+            .regions()
+            .flat_map(|region| region.items.iter())
+            .filter_map(|item| {
+                let entry_namespace = namespace_from_raw(item.name_index)?;
+                if !entry_namespace.is_listed_in(namespace) {
                     None
                 } else {
-                    Some((offset, entry.name_len as usize))
+                    Some((entry_namespace, item.name.as_slice()))
                 }
             })
             .collect();
-        // Include the null byte following each name
+        // Include the prefix and the null byte following each name
         let list_actual_len = target_list
             .iter()
-            .map(|(_, name_len)| name_len)
-            .sum::<usize>()
-            + target_list.len();
+            .map(|(namespace, name)| namespace.prefix().len() + name.len() + 1)
+            .sum::<usize>();
 
         if list_avail_len == 0 {
             return Ok(list_actual_len);
@@ -289,11 +301,10 @@ impl Xattr {
             return_errno_with_message!(Errno::ERANGE, "the xattr list buffer is too small");
         }
 
-        for (offset, name_len) in target_list {
-            let mut reader = self.blocks_buf.reader().to_fallible();
-            let name_reader = reader.skip(offset + XATTR_ENTRY_SIZE).limit(name_len);
-
-            list_writer.write_fallible(name_reader)?;
+        for (namespace, name) in target_list {
+            list_writer
+                .write_fallible(&mut VmReader::from(namespace.prefix().as_bytes()).to_fallible())?;
+            list_writer.write_fallible(&mut VmReader::from(name).to_fallible())?;
             list_writer.write_val(&0u8)?;
         }
 
@@ -301,52 +312,113 @@ impl Xattr {
     }
 
     pub fn remove(&self, name: XattrName) -> Result<()> {
-        self.lazy_init()?;
-
-        let cache = self.cache.upread();
-        let (offset, entry) =
-            cache
-                .find_entry(&name, &self.blocks_buf)
-                .ok_or(Error::with_message(
-                    Errno::ENODATA,
-                    "the target xattr does not exist",
-                ))?;
-
-        let len = entry.total_len();
-        self.blocks_buf
-            .writer()
-            .to_fallible()
-            .skip(offset)
-            .limit(len)
-            .fill_zeros(len)?;
-
-        let mut cache = cache.upgrade();
-        let _ = cache.entries.remove(&offset);
-        let _ = cache.values.remove(&(entry.value_offset as _));
+        self.load()?;
+
+        let (name_index, name_suffix) = name_to_raw(&name);
+        let mut inner = self.inner.write();
+        let is_removed = inner
+            .regions_mut()
+            .any(|region| region.remove(name_index, name_suffix));
+        if !is_removed {
+            return_errno_with_message!(Errno::ENODATA, "the target xattr does not exist");
+        }
 
         Ok(())
     }
 
+    /// Writes back the xattrs.
+    ///
+    /// The inode must not be locked, since the xattr block may be reallocated, which updates
+    /// the inode.
     pub fn flush(&self) -> Result<()> {
-        let cache = self.cache.upread();
-        if cache.is_dirty() {
-            self.fs().block_device().write_blocks(
-                cache.bid,
-                BioSegment::new_from_segment(self.blocks_buf.clone(), BioDirection::ToDevice),
-            )?;
-            cache.upgrade().clear_dirty();
+        let mut inner = self.inner.write();
+        let fs = self.fs();
+
+        if let Some(ibody) = inner.ibody.as_mut()
+            && ibody.is_dirty
+        {
+            let XattrLocation::Inode { offset } = ibody.location else {
+                unreachable!();
+            };
+            fs.write_inode_extra(self.inode().ino(), offset, &ibody.serialize())?;
+            ibody.is_dirty = false;
+        }
+
+        let block = &mut inner.block;
+        if !block.is_dirty {
+            return Ok(());
         }
+
+        // The block may be shared by other inodes. If so, a new block is used instead.
+        if block.ref_count > 1 || block.items.is_empty() {
+            self.release_block(block)?;
+        }
+        let XattrLocation::Block(mut bid) = block.location else {
+            unreachable!();
+        };
+        if !block.items.is_empty() {
+            if bid == 0 {
+                bid = fs
+                    .alloc_blocks(self.inode().block_group_idx(), XATTR_NBLOCKS as _)
+                    .ok_or(Error::new(Errno::ENOSPC))?
+                    .start;
+                block.location = XattrLocation::Block(bid);
+                block.ref_count = 1;
+            }
+            write_block(&fs, bid, &block.serialize())?;
+        }
+        block.is_dirty = false;
+        drop(inner);
+
+        self.inode().set_acl(Bid::new(bid as _));
         Ok(())
     }
 
     pub fn free(&self) -> Result<()> {
-        let cache = self.cache.upread();
-        let bid = cache.bid.to_raw() as Ext2Bid;
+        let mut inner = self.inner.write();
+        let block = &mut inner.block;
+        if block.ref_count == 0 {
+            // The reference count is not known before the block is loaded.
+            let XattrLocation::Block(bid) = block.location else {
+                unreachable!();
+            };
+            if bid == 0 {
+                return Ok(());
+            }
+            let bio_segment = BioSegment::alloc(XATTR_NBLOCKS, BioDirection::FromDevice);
+            self.fs().read_blocks(bid, bio_segment.clone())?;
+            block.ref_count = bio_segment.read_val::<XattrHeader>(0)?.ref_count;
+        }
+        self.release_block(block)
+    }
+
+    /// Drops the reference to the xattr block, and frees the block if it is not
+    /// referenced by other inodes.
+    fn release_block(&self, block: &mut XattrRegion) -> Result<()> {
+        let XattrLocation::Block(bid) = block.location else {
+            unreachable!();
+        };
         if bid == 0 {
             return Ok(());
         }
-        self.fs().free_blocks(bid..bid)?;
-        cache.upgrade().bid = Bid::new(0);
+
+        let fs = self.fs();
+        if block.ref_count > 1 {
+            let bio_segment = BioSegment::alloc(XATTR_NBLOCKS, BioDirection::FromDevice);
+            fs.read_blocks(bid, bio_segment.clone())?;
+            let mut buf = vec![0u8; BLOCK_SIZE];
+            bio_segment.read_bytes(0, &mut buf)?;
+
+            let mut header = XattrHeader::from_bytes(&buf[..XATTR_HEADER_SIZE]);
+            header.ref_count -= 1;
+            buf[..XATTR_HEADER_SIZE].copy_from_slice(header.as_bytes());
+            write_block(&fs, bid, &buf)?;
+        } else {
+            fs.free_blocks(bid..bid + XATTR_NBLOCKS as Ext2Bid)?;
+        }
+
+        block.location = XattrLocation::Block(0);
+        block.ref_count = 0;
         Ok(())
     }
 
@@ -359,121 +431,251 @@ impl Xattr {
     }
 }
 
-impl XattrCache {
-    pub fn new(bid: Bid, capacity_bytes: usize) -> Self {
+impl XattrInner {
+    fn regions(&self) -> impl Iterator<Item = &XattrRegion> {
+        self.ibody.iter().chain(core::iter::once(&self.block))
+    }
+
+    fn regions_mut(&mut self) -> impl Iterator<Item = &mut XattrRegion> {
+        self.ibody
+            .iter_mut()
+            .chain(core::iter::once(&mut self.block))
+    }
+}
+
+impl XattrRegion {
+    fn new(location: XattrLocation, capacity: usize) -> Self {
         Self {
-            bid,
-            header: None,
-            entries: BTreeMap::new(),
-            values: BTreeMap::new(),
-            capacity_bytes,
+            location,
+            capacity,
+            ref_count: 0,
+            items: Vec::new(),
+            is_dirty: false,
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        match self.location {
+            XattrLocation::Block(_) => XATTR_HEADER_SIZE,
+            XattrLocation::Inode { .. } => XATTR_IBODY_HEADER_SIZE,
         }
     }
 
-    pub fn build_entries_from_buf(&mut self, blocks_buf: &USegment) -> Result<()> {
-        let mut offset = XATTR_HEADER_SIZE;
+    /// Returns the offset that the value offsets are relative to.
+    fn value_base(&self) -> usize {
+        match self.location {
+            XattrLocation::Block(_) => 0,
+            XattrLocation::Inode { .. } => XATTR_IBODY_HEADER_SIZE,
+        }
+    }
 
-        while offset < self.capacity_bytes - XATTR_ENTRY_VALUE_GAP - XATTR_ENTRY_SIZE {
-            let entry = blocks_buf.read_val::<XattrEntry>(offset)?;
-            if entry.name_len == 0
-                || XattrNamespace::try_from(entry.name_index).is_err()
-                || entry.value_offset as usize + entry.value_len as usize > self.capacity_bytes
-            {
-                offset += XATTR_ALIGN;
-                continue;
-            }
+    fn find(&self, name_index: u8, name: &[u8]) -> Option<&XattrItem> {
+        self.items
+            .iter()
+            .find(|item| item.name_index == name_index && item.name == name)
+    }
 
-            let _ = self.entries.insert(offset, entry);
-            let _ = self
-                .values
-                .insert(entry.value_offset as _, entry.value_len as _);
-            offset += entry.total_len();
+    /// Inserts or replaces the xattr if there is enough room.
+    fn try_insert(&mut self, new_item: XattrItem) -> bool {
+        let mut items = self.items.clone();
+        match items
+            .iter_mut()
+            .find(|item| item.name_index == new_item.name_index && item.name == new_item.name)
+        {
+            Some(item) => *item = new_item,
+            None => items.push(new_item),
         }
-        Ok(())
+
+        let required_len = self.header_len()
+            + items
+                .iter()
+                .map(|item| XattrEntry::target_len(item.name.len()) + value_len(item))
+                .sum::<usize>()
+            + XATTR_ENTRY_END_SIZE;
+        if required_len > self.capacity {
+            return false;
+        }
+
+        self.items = items;
+        self.is_dirty = true;
+        true
     }
 
-    pub fn find_entry(&self, name: &XattrName, buf: &USegment) -> Option<(usize, XattrEntry)> {
-        let namespace = name.namespace();
-        let name_bytes = name.full_name().as_bytes();
-        let name_len = name_bytes.len();
-        debug_assert!(name_len > 0);
-        let mut name_buf = [0u8; XATTR_NAME_MAX_LEN];
-        for (offset, entry) in &self.entries {
-            if entry.name_index == namespace as u8 && entry.name_len == name_len as u8 {
-                buf.read_bytes(offset + XATTR_ENTRY_SIZE, &mut name_buf[..name_len])
-                    .unwrap();
-                if &name_buf[..name_len] == name_bytes {
-                    return Some((*offset, *entry));
-                }
-            }
+    /// Removes the xattr and returns whether it exists.
+    fn remove(&mut self, name_index: u8, name: &[u8]) -> bool {
+        let len = self.items.len();
+        self.items
+            .retain(|item| item.name_index != name_index || item.name != name);
+        if self.items.len() == len {
+            return false;
         }
-        None
+        self.is_dirty = true;
+        true
     }
 
-    pub fn find_room_for_new_entry(&self, name_len: usize) -> Option<usize> {
-        let target_len = XattrEntry::target_len(name_len);
-
-        // Fast path: find at the entries' end
-        let bottom = self
-            .values
-            .first_key_value()
-            .map(|(offset, _)| *offset)
-            .unwrap_or(self.capacity_bytes)
-            - XATTR_ENTRY_VALUE_GAP;
-        let last_entry_end = self
-            .entries
-            .last_key_value()
-            .map(|(offset, entry)| *offset + entry.total_len())
-            .unwrap_or(XATTR_HEADER_SIZE);
-        if bottom - last_entry_end >= target_len {
-            return Some(last_entry_end);
-        }
-
-        // Slow path: find around the gaps
-        let mut pre_end = XATTR_HEADER_SIZE;
-        for (offset, entry) in &self.entries {
-            if offset - pre_end >= target_len {
-                return Some(pre_end);
-            } else {
-                pre_end = offset + entry.total_len();
+    /// Parses the xattrs from the raw bytes of the region.
+    fn parse(&self, buf: &[u8]) -> Result<Vec<XattrItem>> {
+        let mut items = Vec::new();
+        let mut offset = self.header_len();
+
+        loop {
+            if offset + XATTR_ENTRY_END_SIZE > buf.len() {
+                return_errno_with_message!(Errno::EIO, "the xattr entries are not terminated");
             }
+            if buf[offset..offset + XATTR_ENTRY_END_SIZE]
+                .iter()
+                .all(|byte| *byte == 0)
+            {
+                break;
+            }
+            if offset + XATTR_ENTRY_SIZE > buf.len() {
+                return_errno_with_message!(Errno::EIO, "the xattr entry is out of bounds");
+            }
+
+            let entry = XattrEntry::from_bytes(&buf[offset..offset + XATTR_ENTRY_SIZE]);
+            let name_start = offset + XATTR_ENTRY_SIZE;
+            let name_end = name_start + entry.name_len as usize;
+            let value_start = self.value_base() + entry.value_offset as usize;
+            let value_end = value_start + entry.value_len as usize;
+            if entry.value_inum != 0 {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the xattr values in inodes are not supported"
+                );
+            }
+            if offset + entry.total_len() > buf.len() || value_end > buf.len() {
+                return_errno_with_message!(Errno::EIO, "the xattr entry is out of bounds");
+            }
+
+            items.push(XattrItem {
+                name_index: entry.name_index,
+                name: buf[name_start..name_end].to_vec(),
+                value: buf[value_start..value_end].to_vec(),
+            });
+            offset += entry.total_len();
         }
 
-        None
+        Ok(items)
     }
 
-    pub fn find_room_for_new_value(&self, value_len: usize) -> Option<usize> {
-        let target_len = value_len.align_up(XATTR_ALIGN);
-
-        // Fast path: find at the values' end
-        let top = self
-            .entries
-            .last_key_value()
-            .map(|(offset, entry)| *offset + entry.total_len())
-            .unwrap_or(XATTR_HEADER_SIZE)
-            + XATTR_ENTRY_VALUE_GAP;
-        let last_value_start = self
-            .values
-            .first_key_value()
-            .map(|(offset, _)| *offset)
-            .unwrap_or(self.capacity_bytes);
-
-        if last_value_start - top >= target_len {
-            return Some(last_value_start - target_len);
-        }
-
-        // Slow path: find around the gaps
-        let mut pre_end = self.capacity_bytes;
-        for (offset, value_len) in self.values.iter().rev() {
-            let cur_end = offset + value_len.align_up(XATTR_ALIGN);
-            if pre_end - cur_end >= target_len {
-                return Some(cur_end);
+    /// Serializes the xattrs into the raw bytes of the region.
+    ///
+    /// The entries in the xattr block are sorted, as Linux stops searching once it finds
+    /// an entry after the target.
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; self.capacity];
+        let mut items: Vec<&XattrItem> = self.items.iter().collect();
+        if let XattrLocation::Block(_) = self.location {
+            items.sort_by(|a, b| {
+                (a.name_index, a.name.len(), &a.name).cmp(&(b.name_index, b.name.len(), &b.name))
+            });
+        }
+
+        let mut entry_offset = self.header_len();
+        let mut value_end = self.capacity;
+        let mut block_hash = 0u32;
+        for item in items {
+            let value_offset = if item.value.is_empty() {
+                0
             } else {
-                pre_end = *offset;
+                value_end -= value_len(item);
+                buf[value_end..value_end + item.value.len()].copy_from_slice(&item.value);
+                value_end - self.value_base()
+            };
+
+            let hash = entry_hash(&item.name, &buf[value_end..value_end + value_len(item)]);
+            let entry = XattrEntry {
+                name_len: item.name.len() as _,
+                name_index: item.name_index,
+                value_offset: value_offset as _,
+                value_inum: 0,
+                value_len: item.value.len() as _,
+                hash,
+            };
+            buf[entry_offset..entry_offset + XATTR_ENTRY_SIZE].copy_from_slice(entry.as_bytes());
+            buf[entry_offset + XATTR_ENTRY_SIZE..entry_offset + XATTR_ENTRY_SIZE + item.name.len()]
+                .copy_from_slice(&item.name);
+            entry_offset += entry.total_len();
+
+            block_hash = block_hash.rotate_left(16) ^ hash;
+        }
+
+        match self.location {
+            XattrLocation::Block(_) => {
+                let header = XattrHeader {
+                    ref_count: self.ref_count.max(1),
+                    hash: block_hash,
+                    ..XattrHeader::default()
+                };
+                buf[..XATTR_HEADER_SIZE].copy_from_slice(header.as_bytes());
             }
+            XattrLocation::Inode { .. } if !self.items.is_empty() => {
+                buf[..XATTR_IBODY_HEADER_SIZE].copy_from_slice(&EXT2_XATTR_MAGIC.to_le_bytes());
+            }
+            XattrLocation::Inode { .. } => (),
         }
 
-        None
+        buf
+    }
+}
+
+/// Returns the size of the aligned value.
+fn value_len(item: &XattrItem) -> usize {
+    item.value.len().align_up(XATTR_ALIGN)
+}
+
+/// Computes the hash of an xattr entry, where `value` is padded to 4 bytes.
+fn entry_hash(name: &[u8], value: &[u8]) -> u32 {
+    let mut hash = 0u32;
+    for byte in name {
+        hash = hash.rotate_left(5) ^ (*byte as u32);
+    }
+    for word in value.chunks_exact(size_of::<u32>()) {
+        hash = hash.rotate_left(16) ^ u32::from_le_bytes(word.try_into().unwrap());
+    }
+    hash
+}
+
+fn write_block(fs: &Ext2, bid: Ext2Bid, buf: &[u8]) -> Result<()> {
+    let bio_segment = BioSegment::alloc(XATTR_NBLOCKS, BioDirection::ToDevice);
+    bio_segment.write_bytes(0, buf)?;
+    fs.write_metadata_async(bid, bio_segment)?
+        .wait()
+        .ok_or_else(|| Error::with_message(Errno::EIO, "failed to write the xattr block"))?;
+    Ok(())
+}
+
+/// The name indexes of the namespaces on the device.
+///
+/// The prefix of the namespace is not stored in the name of the entry.
+const NAME_INDEX_USER: u8 = 1;
+const NAME_INDEX_TRUSTED: u8 = 4;
+const NAME_INDEX_SECURITY: u8 = 6;
+const NAME_INDEX_SYSTEM: u8 = 7;
+
+fn name_to_raw<'a>(name: &XattrName<'a>) -> (u8, &'a [u8]) {
+    let namespace = name.namespace();
+    let name_index = match namespace {
+        XattrNamespace::User => NAME_INDEX_USER,
+        XattrNamespace::Trusted => NAME_INDEX_TRUSTED,
+        XattrNamespace::Security => NAME_INDEX_SECURITY,
+        XattrNamespace::System => NAME_INDEX_SYSTEM,
+    };
+    (
+        name_index,
+        &name.full_name().as_bytes()[namespace.prefix().len()..],
+    )
+}
+
+/// Returns the namespace of the name index, or `None` if the xattrs with the name index
+/// are not visible by their names, e.g., the POSIX ACLs.
+fn namespace_from_raw(name_index: u8) -> Option<XattrNamespace> {
+    match name_index {
+        NAME_INDEX_USER => Some(XattrNamespace::User),
+        NAME_INDEX_TRUSTED => Some(XattrNamespace::Trusted),
+        NAME_INDEX_SECURITY => Some(XattrNamespace::Security),
+        NAME_INDEX_SYSTEM => Some(XattrNamespace::System),
+        _ => None,
     }
 }
 
@@ -484,6 +686,7 @@ impl Default for XattrHeader {
             nblocks: XATTR_NBLOCKS as _,
             ref_count: Default::default(),
             hash: Default::default(),
+            checksum: Default::default(),
             reserved: Default::default(),
         }
     }
@@ -4,12 +4,15 @@
 
 //! Opened File Handle
 
+use aster_rights::Rights;
+
 use super::inode_handle::InodeHandle;
 use crate::{
     fs::utils::{AccessMode, FallocMode, InodeMode, IoctlCmd, Metadata, SeekFrom, StatusFlags},
     net::socket::Socket,
     prelude::*,
     process::{signal::Pollable, Gid, Uid},
+    vm::vmo::Vmo,
 };
 
 /// The basic operations defined on a file
@@ -98,6 +101,16 @@ pub trait FileLike: Pollable + Send + Sync + Any {
         return_errno_with_message!(Errno::EOPNOTSUPP, "fallocate is not supported");
     }
 
+    /// Returns the VMO to map when the file is mapped at `offset` by `mmap`,
+    /// along with the corresponding offset in the VMO.
+    ///
+    /// This is for the files that are not backed by inodes (e.g., io_uring
+    /// instances). The files backed by inodes are mapped through their page
+    /// caches instead.
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        return_errno_with_message!(Errno::ENODEV, "mmap is not supported");
+    }
+
     fn as_socket(&self) -> Option<&dyn Socket> {
         None
     }
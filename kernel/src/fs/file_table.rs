@@ -3,11 +3,12 @@
 use core::sync::atomic::{AtomicU8, Ordering};
 
 use aster_util::slot_vec::SlotVec;
+use ostd::sync::{Rcu, RcuOption};
 
 use super::{
     file_handle::FileLike,
     fs_resolver::{FsPath, FsResolver, AT_FDCWD},
-    utils::{AccessMode, InodeMode},
+    utils::{AccessMode, InodeMode, RangeLockOwner},
 };
 use crate::{
     events::{Events, IoEvents, Observer, Subject},
@@ -24,19 +25,24 @@ pub type FileDesc = i32;
 
 pub struct FileTable {
     table: SlotVec<FileTableEntry>,
+    /// The files in `table`, which can be looked up without locking the file table.
+    fd_array: Arc<FdArray>,
+    used_fds: FdBitmap,
     subject: Subject<FdEvents>,
 }
 
 impl FileTable {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             table: SlotVec::new(),
+            fd_array: Arc::new(FdArray::new()),
+            used_fds: FdBitmap::new(),
             subject: Subject::new(),
         }
     }
 
     pub fn new_with_stdio() -> Self {
-        let mut table = SlotVec::new();
+        let mut table = Self::new();
         let fs_resolver = FsResolver::new();
         let tty_path = FsPath::new(AT_FDCWD, "/dev/console").expect("cannot find tty");
         let stdin = {
@@ -54,13 +60,10 @@ impl FileTable {
             let mode = InodeMode::S_IWUSR;
             fs_resolver.open(&tty_path, flags, mode.bits()).unwrap()
         };
-        table.put(FileTableEntry::new(Arc::new(stdin), FdFlags::empty()));
-        table.put(FileTableEntry::new(Arc::new(stdout), FdFlags::empty()));
-        table.put(FileTableEntry::new(Arc::new(stderr), FdFlags::empty()));
-        Self {
-            table,
-            subject: Subject::new(),
-        }
+        table.insert(Arc::new(stdin), FdFlags::empty());
+        table.insert(Arc::new(stdout), FdFlags::empty());
+        table.insert(Arc::new(stderr), FdFlags::empty());
+        table
     }
 
     pub fn len(&self) -> usize {
@@ -79,29 +82,21 @@ impl FileTable {
             .ok_or(Error::with_message(Errno::ENOENT, "No such file"))?;
 
         // Get the lowest-numbered available fd equal to or greater than `new_fd`.
-        let get_min_free_fd = || -> usize {
-            let new_fd = new_fd as usize;
-            if self.table.get(new_fd).is_none() {
-                return new_fd;
-            }
-
-            for idx in new_fd + 1..self.len() {
-                if self.table.get(idx).is_none() {
-                    return idx;
-                }
-            }
-            self.len()
-        };
-
-        let min_free_fd = get_min_free_fd();
+        let min_free_fd = self.used_fds.first_unused_from(new_fd as usize);
+        self.fd_array.set(min_free_fd, Some(&file));
         let entry = FileTableEntry::new(file, flags);
         self.table.put_at(min_free_fd, entry);
+        self.used_fds.insert(min_free_fd);
         Ok(min_free_fd as FileDesc)
     }
 
     pub fn insert(&mut self, item: Arc<dyn FileLike>, flags: FdFlags) -> FileDesc {
+        let fd = self.used_fds.first_unused_from(0);
+        self.fd_array.set(fd, Some(&item));
         let entry = FileTableEntry::new(item, flags);
-        self.table.put(entry) as FileDesc
+        self.table.put_at(fd, entry);
+        self.used_fds.insert(fd);
+        fd as FileDesc
     }
 
     pub fn insert_at(
@@ -110,26 +105,52 @@ impl FileTable {
         item: Arc<dyn FileLike>,
         flags: FdFlags,
     ) -> Option<Arc<dyn FileLike>> {
+        self.fd_array.set(fd as usize, Some(&item));
         let entry = FileTableEntry::new(item, flags);
         let entry = self.table.put_at(fd as usize, entry);
+        self.used_fds.insert(fd as usize);
         if entry.is_some() {
             let events = FdEvents::Close(fd);
             self.notify_fd_events(&events);
             entry.as_ref().unwrap().notify_fd_events(&events);
+            self.release_range_locks(&entry.as_ref().unwrap().file);
         }
         entry.map(|e| e.file)
     }
 
     pub fn close_file(&mut self, fd: FileDesc) -> Option<Arc<dyn FileLike>> {
         let removed_entry = self.table.remove(fd as usize)?;
+        self.fd_array.set(fd as usize, None);
+        self.used_fds.remove(fd as usize);
 
         let events = FdEvents::Close(fd);
         self.notify_fd_events(&events);
         removed_entry.notify_fd_events(&events);
+        self.release_range_locks(&removed_entry.file);
 
         Some(removed_entry.file)
     }
 
+    /// Closes the file descriptors in the range `first..=last`.
+    ///
+    /// The cost is proportional to the number of file descriptors in use, not to the size of
+    /// the range, so closing a huge range (e.g., `0..=u32::MAX`) is cheap.
+    pub fn close_range(&mut self, first: usize, last: usize) -> Vec<Arc<dyn FileLike>> {
+        let fds: Vec<usize> = self.used_fds.iter_used(first, last).collect();
+        fds.into_iter()
+            .filter_map(|fd| self.close_file(fd as FileDesc))
+            .collect()
+    }
+
+    /// Sets the close-on-exec flag of the file descriptors in the range `first..=last`.
+    pub fn set_cloexec_range(&mut self, first: usize, last: usize) {
+        for fd in self.used_fds.iter_used(first, last) {
+            if let Some(entry) = self.table.get(fd) {
+                entry.set_flags(entry.flags() | FdFlags::CLOEXEC);
+            }
+        }
+    }
+
     pub fn close_files_on_exec(&mut self) -> Vec<Arc<dyn FileLike>> {
         self.close_files(|entry| entry.flags().contains(FdFlags::CLOEXEC))
     }
@@ -153,15 +174,33 @@ impl FileTable {
 
         for fd in closed_fds {
             let removed_entry = self.table.remove(fd as usize).unwrap();
+            self.fd_array.set(fd as usize, None);
+            self.used_fds.remove(fd as usize);
             let events = FdEvents::Close(fd);
             self.notify_fd_events(&events);
             removed_entry.notify_fd_events(&events);
+            self.release_range_locks(&removed_entry.file);
             closed_files.push(removed_entry.file);
         }
 
         closed_files
     }
 
+    /// Returns the owner of the traditional POSIX range locks set via this file table.
+    pub fn range_lock_owner(&self) -> RangeLockOwner {
+        RangeLockOwner::FileTable(self as *const Self as usize)
+    }
+
+    /// Releases the traditional POSIX range locks on the file that is being closed.
+    ///
+    /// Following POSIX, closing _any_ file descriptor of a file releases all the locks of the
+    /// owner on the file, even if the locks are set via other file descriptors.
+    fn release_range_locks(&self, file: &Arc<dyn FileLike>) {
+        if let Ok(inode_handle) = file.as_inode_or_err() {
+            inode_handle.release_range_locks(self.range_lock_owner());
+        }
+    }
+
     pub fn get_file(&self, fd: FileDesc) -> Result<&Arc<dyn FileLike>> {
         self.table
             .get(fd as usize)
@@ -169,6 +208,14 @@ impl FileTable {
             .ok_or(Error::with_message(Errno::EBADF, "fd not exits"))
     }
 
+    /// Returns the files that can be looked up without locking the file table.
+    ///
+    /// This allows the threads sharing the file table to look up files under RCU. See
+    /// [`FdArray`] for details.
+    pub fn fd_array(&self) -> &Arc<FdArray> {
+        &self.fd_array
+    }
+
     pub fn get_entry(&self, fd: FileDesc) -> Result<&FileTableEntry> {
         self.table
             .get(fd as usize)
@@ -208,8 +255,15 @@ impl Default for FileTable {
 
 impl Clone for FileTable {
     fn clone(&self) -> Self {
+        let fd_array = FdArray::new();
+        for (fd, entry) in self.table.idxes_and_items() {
+            fd_array.set(fd, Some(&entry.file));
+        }
+
         Self {
             table: self.table.clone(),
+            fd_array: Arc::new(fd_array),
+            used_fds: self.used_fds.clone(),
             subject: Subject::new(),
         }
     }
@@ -225,6 +279,174 @@ impl Drop for FileTable {
     }
 }
 
+/// The files indexed by file descriptors, which can be looked up under RCU.
+///
+/// This mirrors the files in a [`FileTable`], and it is only updated by the file table, which is
+/// write-locked if it is shared. So the threads sharing the file table can look up files without
+/// taking the lock of the file table, like Linux does with its `fdtable`.
+///
+/// The slots hold weak references to the files. Otherwise, closing a file would defer dropping
+/// the file until the end of the RCU grace period, when blocking is not allowed.
+pub struct FdArray {
+    slots: Rcu<Box<Vec<RcuOption<Box<Weak<dyn FileLike>>>>>>,
+}
+
+impl FdArray {
+    const MIN_LEN: usize = 64;
+
+    fn new() -> Self {
+        Self {
+            slots: Rcu::new(Box::new(Vec::new())),
+        }
+    }
+
+    /// Looks up the file of the file descriptor.
+    pub fn get_file(&self, fd: FileDesc) -> Result<Arc<dyn FileLike>> {
+        let slots = self.slots.read();
+        let file = slots
+            .get()
+            .get(fd as usize)
+            .and_then(|slot| slot.read().get().and_then(|file| file.upgrade()));
+        file.ok_or(Error::with_message(Errno::EBADF, "fd not exits"))
+    }
+
+    /// Sets the file of the file descriptor, or clears it if `file` is `None`.
+    ///
+    /// The updates are serialized by the exclusive access to the file table.
+    fn set(&self, fd: usize, file: Option<&Arc<dyn FileLike>>) {
+        let len = self.slots.read().get().len();
+        if fd >= len {
+            if file.is_none() {
+                return;
+            }
+            self.grow(fd + 1);
+        }
+
+        let slots = self.slots.read();
+        slots.get()[fd].update(file.map(|file| Box::new(Arc::downgrade(file))));
+    }
+
+    /// Replaces the slots with larger ones, so that there are at least `min_len` slots.
+    fn grow(&self, min_len: usize) {
+        let slots = self.slots.read();
+        let old_slots = slots.get();
+
+        let new_len = min_len.max(old_slots.len() * 2).max(Self::MIN_LEN);
+        let mut new_slots = Vec::with_capacity(new_len);
+        new_slots.extend(old_slots.iter().map(|slot| {
+            RcuOption::new(slot.read().get().map(|file| Box::new(Weak::clone(&**file))))
+        }));
+        new_slots.resize_with(new_len, RcuOption::new_none);
+
+        // The readers may still see the old slots until the end of the RCU grace period. This is
+        // fine because the old slots are not updated anymore, so the readers see the files before
+        // the update, just as if they looked up the files a bit earlier.
+        drop(slots);
+        self.slots.update(Box::new(new_slots));
+    }
+}
+
+/// A two-level bitmap that tracks the file descriptors in use.
+///
+/// Each bit in `used` corresponds to a file descriptor, and each bit in `full` corresponds to a
+/// word in `used` and is set if the word is full. So looking for an unused file descriptor skips
+/// 4096 used file descriptors at a time, which keeps `open` fast even if a process has tens of
+/// thousands of file descriptors.
+#[derive(Clone)]
+struct FdBitmap {
+    used: Vec<u64>,
+    full: Vec<u64>,
+}
+
+impl FdBitmap {
+    const BITS: usize = u64::BITS as usize;
+
+    const fn new() -> Self {
+        Self {
+            used: Vec::new(),
+            full: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, fd: usize) {
+        let (word, bit) = (fd / Self::BITS, fd % Self::BITS);
+        if word >= self.used.len() {
+            self.used.resize(word + 1, 0);
+            self.full.resize(self.used.len().div_ceil(Self::BITS), 0);
+        }
+
+        self.used[word] |= 1 << bit;
+        if self.used[word] == u64::MAX {
+            self.full[word / Self::BITS] |= 1 << (word % Self::BITS);
+        }
+    }
+
+    fn remove(&mut self, fd: usize) {
+        let (word, bit) = (fd / Self::BITS, fd % Self::BITS);
+        if word >= self.used.len() {
+            return;
+        }
+
+        self.used[word] &= !(1 << bit);
+        self.full[word / Self::BITS] &= !(1 << (word % Self::BITS));
+    }
+
+    /// Returns the lowest unused file descriptor that is equal to or greater than `start`.
+    fn first_unused_from(&self, start: usize) -> usize {
+        let mut word = start / Self::BITS;
+        if word >= self.used.len() {
+            return start;
+        }
+
+        let unused = !self.used[word] & (u64::MAX << (start % Self::BITS));
+        if unused != 0 {
+            return word * Self::BITS + unused.trailing_zeros() as usize;
+        }
+
+        word += 1;
+        while word < self.used.len() {
+            let full_word = word / Self::BITS;
+            let not_full = !self.full[full_word] & (u64::MAX << (word % Self::BITS));
+            if not_full == 0 {
+                word = (full_word + 1) * Self::BITS;
+                continue;
+            }
+
+            word = full_word * Self::BITS + not_full.trailing_zeros() as usize;
+            if word < self.used.len() {
+                return word * Self::BITS + (!self.used[word]).trailing_zeros() as usize;
+            }
+        }
+
+        self.used.len() * Self::BITS
+    }
+
+    /// Returns an iterator over the used file descriptors in the range `first..=last`.
+    fn iter_used(&self, first: usize, last: usize) -> impl Iterator<Item = usize> + '_ {
+        let first_word = first / Self::BITS;
+        let last_word = (last / Self::BITS).min(self.used.len().saturating_sub(1));
+
+        self.used
+            .iter()
+            .enumerate()
+            .skip(first_word)
+            .take((last_word + 1).saturating_sub(first_word))
+            .filter(|(_, bits)| **bits != 0)
+            .flat_map(|(word, bits)| {
+                let mut bits = *bits;
+                core::iter::from_fn(move || {
+                    if bits == 0 {
+                        return None;
+                    }
+                    let bit = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    Some(word * Self::BITS + bit)
+                })
+            })
+            .filter(move |fd| (first..=last).contains(fd))
+    }
+}
+
 /// A helper trait that provides methods to operate the file table.
 pub trait WithFileTable {
     /// Calls `f` with the file table.
@@ -255,9 +477,10 @@ impl WithFileTable for FileTableRefMut<'_> {
 /// If the file table is not shared with another thread, this macro will be free of locks
 /// ([`RwArc::read`]) and free of reference counting ([`Arc::clone`]).
 ///
-/// If the file table is shared, the read lock is taken, the file is cloned, and then the read lock
-/// is released. Cloning and releasing the lock is necessary because we cannot hold such locks when
-/// operating on files, since many operations on files can block.
+/// If the file table is shared, the file is looked up under RCU via the [`FdArray`] without taking
+/// the lock of the file table, and then the file is cloned. Cloning is necessary because we cannot
+/// stay in the RCU read-side critical section when operating on files, since many operations on
+/// files can block.
 ///
 /// Note: This has to be a macro due to a limitation in the Rust borrow check implementation. Once
 /// <https://github.com/rust-lang/rust/issues/58910> is fixed, we can try to convert this macro to
@@ -269,22 +492,18 @@ macro_rules! get_file_fast {
     ($file_table:expr, $file_desc:expr) => {{
         use alloc::borrow::Cow;
 
-        use ostd::sync::RwArc;
-        use $crate::{
-            fs::file_table::{FileDesc, FileTable},
-            process::posix_thread::FileTableRefMut,
-        };
+        use $crate::{fs::file_table::FileDesc, process::posix_thread::FileTableRefMut};
 
         let file_table: &mut FileTableRefMut<'_> = $file_table;
-        let file_table: &mut RwArc<FileTable> = file_table.unwrap();
         let file_desc: FileDesc = $file_desc;
 
-        if let Some(inner) = file_table.get() {
+        if let Some(inner) = file_table.unwrap().get() {
             // Fast path: The file table is not shared, we can get the file in a lockless way.
             Cow::Borrowed(inner.get_file(file_desc)?)
         } else {
-            // Slow path: The file table is shared, we need to hold the lock and clone the file.
-            Cow::Owned(file_table.read().get_file(file_desc)?.clone())
+            // Slow path: The file table is shared, we need to look up the file under RCU and
+            // clone the file.
+            Cow::Owned(file_table.fd_array().get_file(file_desc)?)
         }
     }};
 }
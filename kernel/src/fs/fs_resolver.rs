@@ -7,9 +7,13 @@ use ostd::task::Task;
 use super::{
     file_table::{get_file_fast, FileDesc},
     inode_handle::InodeHandle,
-    path::Dentry,
+    notify,
+    path::{is_dotdot, Dentry},
     rootfs::root_mount,
-    utils::{AccessMode, CreationFlags, InodeMode, InodeType, StatusFlags, PATH_MAX, SYMLINKS_MAX},
+    utils::{
+        AccessMode, CreationFlags, InodeFlags, InodeMode, InodeType, StatusFlags, PATH_MAX,
+        SYMLINKS_MAX,
+    },
 };
 use crate::{prelude::*, process::posix_thread::AsThreadLocal};
 
@@ -54,11 +58,23 @@ impl FsResolver {
 
     /// Opens or creates a file inode handler.
     pub fn open(&self, path: &FsPath, flags: u32, mode: u16) -> Result<InodeHandle> {
+        self.open_with_resolve_flags(path, flags, mode, ResolveFlags::empty())
+    }
+
+    /// Opens or creates a file inode handler with restrictions on the path resolution.
+    pub fn open_with_resolve_flags(
+        &self,
+        path: &FsPath,
+        flags: u32,
+        mode: u16,
+        resolve_flags: ResolveFlags,
+    ) -> Result<InodeHandle> {
         let open_args = OpenArgs::from_flags_and_mode(flags, mode)?;
 
         let follow_tail_link = open_args.follow_tail_link();
         let stop_on_parent = false;
         let mut lookup_ctx = LookupCtx::new(follow_tail_link, stop_on_parent);
+        lookup_ctx.resolve_flags = resolve_flags;
 
         let lookup_res = self.lookup_inner(path, &mut lookup_ctx);
 
@@ -73,6 +89,9 @@ impl FsResolver {
             Err(e) => return Err(e),
         };
 
+        if !open_args.status_flags.contains(StatusFlags::O_PATH) {
+            notify::on_open(inode_handle.dentry());
+        }
         Ok(inode_handle)
     }
 
@@ -112,8 +131,37 @@ impl FsResolver {
             );
         }
 
+        let is_write = (open_args.access_mode.is_writable()
+            || creation_flags.contains(CreationFlags::O_TRUNC))
+            && !open_args.status_flags.contains(StatusFlags::O_PATH);
+        // Like Linux, the special files can still be written on a read-only mount,
+        // since writing them does not modify the file system.
+        let is_regular_or_dir = matches!(inode_type, InodeType::File | InodeType::Dir);
+        if is_regular_or_dir && is_write {
+            target_dentry.check_writable_mount()?;
+        }
+        if is_write {
+            let inode_flags = target_dentry.inode().flags();
+            if inode_flags.contains(InodeFlags::IMMUTABLE) {
+                return_errno_with_message!(Errno::EPERM, "the file is immutable");
+            }
+            if inode_flags.contains(InodeFlags::APPEND)
+                && (!open_args.status_flags.contains(StatusFlags::O_APPEND)
+                    || creation_flags.contains(CreationFlags::O_TRUNC))
+            {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the append-only file can only be opened for appending"
+                );
+            }
+        }
+
+        if !open_args.status_flags.contains(StatusFlags::O_PATH) {
+            notify::on_open_perm(&target_dentry)?;
+        }
         if creation_flags.contains(CreationFlags::O_TRUNC) {
             target_dentry.resize(0)?;
+            notify::on_modify(&target_dentry);
         }
         InodeHandle::new(target_dentry, open_args.access_mode, open_args.status_flags)
     }
@@ -140,6 +188,7 @@ impl FsResolver {
         let tail_file_name = lookup_ctx.tail_file_name().unwrap();
         let new_dentry =
             parent.new_fs_child(&tail_file_name, InodeType::File, open_args.inode_mode)?;
+        notify::on_open_perm(&new_dentry)?;
         // Don't check access mode for newly created file
         InodeHandle::new_unchecked_access(new_dentry, open_args.access_mode, open_args.status_flags)
     }
@@ -159,26 +208,29 @@ impl FsResolver {
     }
 
     fn lookup_inner(&self, path: &FsPath, lookup_ctx: &mut LookupCtx) -> Result<Dentry> {
+        if lookup_ctx.is_scoped() {
+            // The lookup is scoped to the directory of `dirfd`, even if the path is absolute.
+            let scope = if path.dirfd == AT_FDCWD {
+                self.cwd.clone()
+            } else {
+                lookup_fd(path.dirfd)?
+            };
+            lookup_ctx.scope = Some(scope);
+        }
+
         let dentry = match path.inner {
             FsPathInner::Absolute(path) => {
-                self.lookup_from_parent(&self.root, path.trim_start_matches('/'), lookup_ctx)?
+                let root = lookup_ctx.root(&self.root)?;
+                self.lookup_from_parent(&root, path.trim_start_matches('/'), lookup_ctx)?
             }
             FsPathInner::CwdRelative(path) => {
                 self.lookup_from_parent(&self.cwd, path, lookup_ctx)?
             }
             FsPathInner::Cwd => self.cwd.clone(),
             FsPathInner::FdRelative(fd, path) => {
-                let task = Task::current().unwrap();
-                let mut file_table = task.as_thread_local().unwrap().borrow_file_table_mut();
-                let file = get_file_fast!(&mut file_table, fd);
-                self.lookup_from_parent(file.as_inode_or_err()?.dentry(), path, lookup_ctx)?
-            }
-            FsPathInner::Fd(fd) => {
-                let task = Task::current().unwrap();
-                let mut file_table = task.as_thread_local().unwrap().borrow_file_table_mut();
-                let file = get_file_fast!(&mut file_table, fd);
-                file.as_inode_or_err()?.dentry().clone()
+                self.lookup_from_parent(&lookup_fd(fd)?, path, lookup_ctx)?
             }
+            FsPathInner::Fd(fd) => lookup_fd(fd)?,
         };
 
         Ok(dentry)
@@ -195,6 +247,9 @@ impl FsResolver {
     /// If `follow_tail_link` is true and the trailing component is a symlink,
     /// it will be followed.
     /// Symlinks in earlier components of the path will always be followed.
+    ///
+    /// The restrictions specified by the resolve flags of `lookup_ctx` are checked
+    /// at each step, so that they cannot be bypassed by the symlinks or `..`.
     #[expect(clippy::redundant_closure)]
     fn lookup_from_parent(
         &self,
@@ -235,7 +290,7 @@ impl FsResolver {
                 return Ok(dentry);
             }
 
-            let next_dentry = match dentry.lookup(next_name) {
+            let next_dentry = match self.lookup_next(&dentry, next_name, lookup_ctx) {
                 Ok(dentry) => dentry,
                 Err(e) => {
                     if next_is_tail && e.error() == Errno::ENOENT && lookup_ctx.tail_file.is_none()
@@ -253,6 +308,7 @@ impl FsResolver {
                 if follows >= SYMLINKS_MAX {
                     return_errno_with_message!(Errno::ELOOP, "too many symlinks");
                 }
+                lookup_ctx.check_symlink(&next_dentry)?;
                 let link_path_remain = {
                     let mut tmp_link_path = next_dentry.inode().read_link()?;
                     if tmp_link_path.is_empty() {
@@ -269,7 +325,9 @@ impl FsResolver {
 
                 // Change the dentry and relative path according to symlink
                 if link_path_remain.starts_with('/') {
-                    dentry = self.root.clone();
+                    let root = lookup_ctx.root(&self.root)?;
+                    lookup_ctx.check_mount_crossing(&dentry, &root)?;
+                    dentry = root;
                 }
                 let link_path = link_path_opt.get_or_insert_with(|| String::new());
                 link_path.clear();
@@ -289,6 +347,46 @@ impl FsResolver {
         Ok(dentry)
     }
 
+    /// Lookups the next component of a path from the directory `dentry`.
+    fn lookup_next(&self, dentry: &Dentry, name: &str, lookup_ctx: &LookupCtx) -> Result<Dentry> {
+        let resolve_flags = lookup_ctx.resolve_flags;
+
+        let next_dentry = if is_dotdot(name) {
+            let root = lookup_ctx.scope.as_ref().unwrap_or(&self.root);
+            if is_same_dentry(dentry, root) {
+                if resolve_flags.contains(ResolveFlags::RESOLVE_BENEATH) {
+                    return_errno_with_message!(
+                        Errno::EXDEV,
+                        "the path escapes from the starting directory"
+                    );
+                }
+                // Like Linux, the parent of the root directory is itself.
+                dentry.clone()
+            } else {
+                let parent = dentry.lookup(name)?;
+                // The directory may have been moved out of the scope by a concurrent rename.
+                if lookup_ctx
+                    .scope
+                    .as_ref()
+                    .is_some_and(|scope| !is_beneath(&parent, scope))
+                {
+                    return_errno_with_message!(
+                        Errno::EXDEV,
+                        "the path escapes from the starting directory"
+                    );
+                }
+                parent
+            }
+        } else if resolve_flags.contains(ResolveFlags::RESOLVE_CACHED) {
+            dentry.lookup_cached(name)?
+        } else {
+            dentry.lookup(name)?
+        };
+
+        lookup_ctx.check_mount_crossing(dentry, &next_dentry)?;
+        Ok(next_dentry)
+    }
+
     /// Lookups the target parent directory dentry and
     /// the base file name according to the given `path`.
     ///
@@ -355,6 +453,51 @@ impl Default for FsResolver {
     }
 }
 
+/// Gets the dentry of an opened file.
+fn lookup_fd(fd: FileDesc) -> Result<Dentry> {
+    let task = Task::current().unwrap();
+    let mut file_table = task.as_thread_local().unwrap().borrow_file_table_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+    Ok(file.as_inode_or_err()?.dentry().clone())
+}
+
+/// Checks whether two dentries refer to the same location in the mount tree.
+fn is_same_dentry(this: &Dentry, other: &Dentry) -> bool {
+    Arc::ptr_eq(this.mount_node(), other.mount_node()) && this.key() == other.key()
+}
+
+/// Checks whether `dentry` is `ancestor` or one of its descendants in the mount tree.
+fn is_beneath(dentry: &Dentry, ancestor: &Dentry) -> bool {
+    let mut current = Some(dentry.clone());
+    while let Some(dentry) = current {
+        if is_same_dentry(&dentry, ancestor) {
+            return true;
+        }
+        current = dentry.effective_parent();
+    }
+    false
+}
+
+bitflags! {
+    /// The flags that restrict the path resolution.
+    ///
+    /// Reference: <https://man7.org/linux/man-pages/man2/openat2.2.html>.
+    pub struct ResolveFlags: u64 {
+        /// Do not cross any mount points.
+        const RESOLVE_NO_XDEV = 0x01;
+        /// Do not follow any magic links.
+        const RESOLVE_NO_MAGICLINKS = 0x02;
+        /// Do not follow any symlinks.
+        const RESOLVE_NO_SYMLINKS = 0x04;
+        /// Do not escape from the starting directory.
+        const RESOLVE_BENEATH = 0x08;
+        /// Treat the starting directory as the root directory.
+        const RESOLVE_IN_ROOT = 0x10;
+        /// Only resolve the path with the cached dentries.
+        const RESOLVE_CACHED = 0x20;
+    }
+}
+
 /// Context information describing one lookup operation.
 #[derive(Debug)]
 struct LookupCtx {
@@ -363,6 +506,10 @@ struct LookupCtx {
     // (file_name, file_is_dir)
     tail_file: Option<(String, bool)>,
     parent: Option<Dentry>,
+    resolve_flags: ResolveFlags,
+    /// The directory that the lookup cannot escape from
+    /// if `RESOLVE_BENEATH` or `RESOLVE_IN_ROOT` is specified.
+    scope: Option<Dentry>,
 }
 
 impl LookupCtx {
@@ -372,7 +519,62 @@ impl LookupCtx {
             stop_on_parent,
             tail_file: None,
             parent: None,
+            resolve_flags: ResolveFlags::empty(),
+            scope: None,
+        }
+    }
+
+    /// Returns whether the lookup is scoped to the starting directory.
+    fn is_scoped(&self) -> bool {
+        self.resolve_flags
+            .intersects(ResolveFlags::RESOLVE_BENEATH | ResolveFlags::RESOLVE_IN_ROOT)
+    }
+
+    /// Returns the directory that absolute paths start from,
+    /// including the targets of absolute symlinks.
+    fn root(&self, fs_root: &Dentry) -> Result<Dentry> {
+        if self.resolve_flags.contains(ResolveFlags::RESOLVE_BENEATH) {
+            return_errno_with_message!(
+                Errno::EXDEV,
+                "absolute paths are not allowed with RESOLVE_BENEATH"
+            );
+        }
+        Ok(self.scope.as_ref().unwrap_or(fs_root).clone())
+    }
+
+    /// Checks whether the symlink can be followed.
+    fn check_symlink(&self, symlink: &Dentry) -> Result<()> {
+        if self
+            .resolve_flags
+            .contains(ResolveFlags::RESOLVE_NO_SYMLINKS)
+        {
+            return_errno_with_message!(Errno::ELOOP, "symlinks are not allowed");
+        }
+        if !symlink.inode().is_magic_link() {
+            return Ok(());
+        }
+        if self
+            .resolve_flags
+            .contains(ResolveFlags::RESOLVE_NO_MAGICLINKS)
+        {
+            return_errno_with_message!(Errno::ELOOP, "magic links are not allowed");
+        }
+        // Like Linux, magic links are not safe for scoped lookups.
+        if self.is_scoped() {
+            return_errno_with_message!(Errno::EXDEV, "magic links are not allowed");
+        }
+        Ok(())
+    }
+
+    /// Checks whether the lookup can go from `from` to `to`
+    /// if they belong to different mounts.
+    fn check_mount_crossing(&self, from: &Dentry, to: &Dentry) -> Result<()> {
+        if self.resolve_flags.contains(ResolveFlags::RESOLVE_NO_XDEV)
+            && !Arc::ptr_eq(from.mount_node(), to.mount_node())
+        {
+            return_errno_with_message!(Errno::EXDEV, "crossing mount points is not allowed");
         }
+        Ok(())
     }
 
     pub fn tail_file_name(&self) -> Option<String> {
@@ -438,6 +640,7 @@ impl OpenArgs {
 /// Path in the file system.
 #[derive(Debug)]
 pub struct FsPath<'a> {
+    dirfd: FileDesc,
     inner: FsPathInner<'a>,
 }
 
@@ -481,6 +684,7 @@ impl<'a> FsPath<'a> {
         };
 
         Ok(Self {
+            dirfd,
             inner: fs_path_inner,
         })
     }
@@ -0,0 +1,513 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The connection between the kernel and a FUSE daemon.
+//!
+//! A connection is created when `/dev/fuse` is opened. The daemon reads requests from the file
+//! and writes replies to it, while the file system sends requests through the connection and
+//! waits for the replies that match the unique IDs of the requests.
+
+use ostd::{sync::WaitQueue, task::Task};
+
+use super::abi::{
+    parse, FuseInHeader, FuseInitIn, FuseInitOut, FuseInterruptIn, FuseOpcode, FuseOutHeader,
+    InitFlags, FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION, FUSE_MIN_READ_BUFFER,
+};
+use crate::{
+    events::IoEvents,
+    fs::inode_handle::FileIo,
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+};
+
+/// The flags that the kernel supports.
+const SUPPORTED_INIT_FLAGS: InitFlags = InitFlags::ASYNC_READ
+    .union(InitFlags::ATOMIC_O_TRUNC)
+    .union(InitFlags::BIG_WRITES)
+    .union(InitFlags::DO_READDIRPLUS)
+    .union(InitFlags::NO_OPEN_SUPPORT)
+    .union(InitFlags::NO_OPENDIR_SUPPORT);
+
+/// The maximum size of the data in a read or write request.
+pub(super) const MAX_IO_SIZE: u32 = 128 * 1024;
+
+/// A connection to a FUSE daemon.
+pub struct FuseConn {
+    state: SpinLock<ConnState>,
+    /// The state of the device file, i.e., whether there are requests to read.
+    pollee: Pollee,
+    /// The threads that wait for the replies or the initialization.
+    reply_queue: WaitQueue,
+}
+
+struct ConnState {
+    next_unique: u64,
+    /// The requests that have not been read by the daemon.
+    pending: VecDeque<(u64, Vec<u8>)>,
+    /// The replies of the requests that are sent, where `None` means that the reply has not
+    /// arrived yet.
+    replies: BTreeMap<u64, Option<Result<Vec<u8>>>>,
+    /// The requests whose replies are ignored.
+    background: BTreeSet<u64>,
+    init: InitState,
+    is_aborted: bool,
+}
+
+enum InitState {
+    /// The connection is not used by any mount.
+    Unused,
+    /// The `FUSE_INIT` request with the unique ID is sent.
+    Sent(u64),
+    Done(FuseInitInfo),
+    /// The daemon replies with an error or an unsupported version.
+    Failed,
+}
+
+/// The parameters negotiated by `FUSE_INIT`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct FuseInitInfo {
+    pub flags: InitFlags,
+    pub max_write: u32,
+}
+
+impl FuseConn {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinLock::new(ConnState {
+                // Like Linux, the unique IDs start from 2, since 0 is for notifications.
+                next_unique: 2,
+                pending: VecDeque::new(),
+                replies: BTreeMap::new(),
+                background: BTreeSet::new(),
+                init: InitState::Unused,
+                is_aborted: false,
+            }),
+            pollee: Pollee::new(),
+            reply_queue: WaitQueue::new(),
+        })
+    }
+
+    /// Sends the `FUSE_INIT` request without waiting for the reply.
+    ///
+    /// The daemon usually calls `mount` before it starts to read requests, so the reply is
+    /// waited for when the first request is sent.
+    pub(super) fn start_init(&self) -> Result<()> {
+        let init_in = FuseInitIn {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            max_readahead: MAX_IO_SIZE,
+            flags: SUPPORTED_INIT_FLAGS.bits(),
+        };
+
+        let mut state = self.state.lock();
+        if state.is_aborted {
+            return_errno_with_message!(Errno::ENOTCONN, "the FUSE connection is aborted");
+        }
+        if !matches!(state.init, InitState::Unused) {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE connection is already mounted");
+        }
+        let unique = state.push_request(FuseOpcode::Init, 0, &[init_in.as_bytes()]);
+        state.replies.insert(unique, None);
+        state.init = InitState::Sent(unique);
+        drop(state);
+
+        self.pollee.notify(IoEvents::IN);
+        Ok(())
+    }
+
+    /// Waits until the connection is initialized.
+    pub(super) fn wait_init(&self) -> Result<FuseInitInfo> {
+        self.reply_queue.pause_until(|| {
+            let state = self.state.lock();
+            if state.is_aborted {
+                return Some(Err(Error::with_message(
+                    Errno::ENOTCONN,
+                    "the FUSE connection is aborted",
+                )));
+            }
+            match state.init {
+                InitState::Done(info) => Some(Ok(info)),
+                InitState::Failed => Some(Err(Error::with_message(
+                    Errno::ECONNREFUSED,
+                    "the FUSE daemon fails to initialize",
+                ))),
+                InitState::Unused | InitState::Sent(_) => None,
+            }
+        })?
+    }
+
+    /// Sends a request and waits for the reply.
+    ///
+    /// The arguments are concatenated as the payload of the request. The payload of the reply
+    /// is returned, or the error that the daemon replies with.
+    pub(super) fn request(
+        &self,
+        opcode: FuseOpcode,
+        nodeid: u64,
+        args: &[&[u8]],
+    ) -> Result<Vec<u8>> {
+        self.wait_init()?;
+
+        let unique = {
+            let mut state = self.state.lock();
+            if state.is_aborted {
+                return_errno_with_message!(Errno::ENOTCONN, "the FUSE connection is aborted");
+            }
+            let unique = state.push_request(opcode, nodeid, args);
+            state.replies.insert(unique, None);
+            unique
+        };
+        self.pollee.notify(IoEvents::IN);
+
+        let take_reply = || {
+            let mut state = self.state.lock();
+            if let Some(reply) = state.take_reply(unique) {
+                return Some(reply);
+            }
+            if state.is_aborted {
+                state.replies.remove(&unique);
+                return Some(Err(Error::with_message(
+                    Errno::ENOTCONN,
+                    "the FUSE connection is aborted",
+                )));
+            }
+            None
+        };
+
+        match self.reply_queue.pause_until(take_reply) {
+            Err(err) if err.error() == Errno::EINTR => (),
+            result => return result?,
+        }
+
+        // Like Linux, a request that is not read by the daemon is canceled. Otherwise, the
+        // daemon is notified of the interruption and the reply is still waited for.
+        {
+            let mut state = self.state.lock();
+            if let Some(index) = state.pending.iter().position(|(id, _)| *id == unique) {
+                state.pending.remove(index);
+                state.replies.remove(&unique);
+                return_errno_with_message!(Errno::EINTR, "the FUSE request is interrupted");
+            }
+            let interrupt_in = FuseInterruptIn { unique };
+            state.push_request(FuseOpcode::Interrupt, 0, &[interrupt_in.as_bytes()]);
+        }
+        self.pollee.notify(IoEvents::IN);
+
+        self.reply_queue.wait_until(take_reply)
+    }
+
+    /// Sends a request without waiting for the reply.
+    ///
+    /// This is for the requests whose results do not matter, e.g., `FUSE_FORGET`, which has no
+    /// reply, and `FUSE_RELEASE`, which is sent when an inode is dropped.
+    pub(super) fn send_background(&self, opcode: FuseOpcode, nodeid: u64, args: &[&[u8]]) {
+        let mut state = self.state.lock();
+        if state.is_aborted {
+            return;
+        }
+        let unique = state.push_request(opcode, nodeid, args);
+        if opcode != FuseOpcode::Forget {
+            state.background.insert(unique);
+        }
+        drop(state);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Aborts the connection.
+    ///
+    /// All the requests fail with `ENOTCONN`, and the daemon fails to read requests with
+    /// `ENODEV`.
+    pub fn abort(&self) {
+        let mut state = self.state.lock();
+        state.is_aborted = true;
+        state.pending.clear();
+        state.background.clear();
+        drop(state);
+
+        self.reply_queue.wake_all();
+        self.pollee.notify(IoEvents::IN | IoEvents::ERR);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state.lock().is_aborted
+    }
+
+    /// Takes a request that has not been read, whose length is at most `max_len`.
+    ///
+    /// This is for the transports that carry the requests, e.g., `/dev/fuse`.
+    pub(super) fn pop_request(&self, max_len: usize) -> Result<Option<(u64, Vec<u8>)>> {
+        let mut state = self.state.lock();
+        if state.is_aborted {
+            return_errno_with_message!(Errno::ENODEV, "the FUSE connection is aborted");
+        }
+
+        while let Some((unique, request)) = state.pending.pop_front() {
+            if request.len() > max_len {
+                // Like Linux, the request that cannot be read fails.
+                state.complete(
+                    unique,
+                    Err(Error::with_message(
+                        Errno::EIO,
+                        "the FUSE request is too large for the daemon",
+                    )),
+                );
+                self.reply_queue.wake_all();
+                continue;
+            }
+
+            if state.pending.is_empty() {
+                self.pollee.invalidate();
+            }
+            return Ok(Some((unique, request)));
+        }
+
+        Ok(None)
+    }
+
+    /// Fails a request that is taken with [`Self::pop_request`] but cannot be delivered.
+    pub(super) fn fail_request(&self, unique: u64, err: Error) {
+        self.state.lock().complete(unique, Err(err));
+        self.reply_queue.wake_all();
+    }
+
+    /// Completes a request with the reply, which begins with a `FuseOutHeader`.
+    pub(super) fn push_reply(&self, reply: &[u8]) -> Result<()> {
+        let header = parse::<FuseOutHeader>(reply)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the FUSE reply is too short"))?;
+        if header.len as usize != reply.len() {
+            return_errno_with_message!(Errno::EINVAL, "the length of the FUSE reply is invalid");
+        }
+
+        // Notifications are not supported, but they do not affect the requests.
+        if header.unique == 0 {
+            return Ok(());
+        }
+
+        let payload = &reply[size_of::<FuseOutHeader>()..];
+        let reply = match header.error {
+            0 => Ok(payload.to_vec()),
+            -511..=-1 => {
+                if !payload.is_empty() {
+                    return_errno_with_message!(Errno::EINVAL, "the FUSE error has a payload");
+                }
+                let errno = Errno::try_from(-header.error).unwrap_or(Errno::EIO);
+                Err(Error::with_message(
+                    errno,
+                    "the FUSE daemon replies with an error",
+                ))
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the FUSE error is invalid"),
+        };
+
+        let mut state = self.state.lock();
+        if state.is_aborted {
+            return_errno_with_message!(Errno::ENODEV, "the FUSE connection is aborted");
+        }
+        if !state.complete(header.unique, reply) {
+            return_errno_with_message!(Errno::ENOENT, "the FUSE request is not found");
+        }
+        drop(state);
+
+        self.reply_queue.wake_all();
+        Ok(())
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let Some((unique, request)) = self.pop_request(writer.avail())? else {
+            return_errno_with_message!(Errno::EAGAIN, "there are no FUSE requests");
+        };
+
+        if let Err(err) = writer.write_fallible(&mut VmReader::from(request.as_slice())) {
+            self.fail_request(
+                unique,
+                Error::with_message(Errno::EIO, "the FUSE request cannot be read by the daemon"),
+            );
+            return Err(err.into());
+        }
+        Ok(request.len())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let state = self.state.lock();
+
+        // The file is always writable.
+        let mut events = IoEvents::OUT;
+        if state.is_aborted {
+            events |= IoEvents::IN | IoEvents::ERR;
+        } else if !state.pending.is_empty() {
+            events |= IoEvents::IN;
+        }
+
+        events
+    }
+}
+
+impl ConnState {
+    fn push_request(&mut self, opcode: FuseOpcode, nodeid: u64, args: &[&[u8]]) -> u64 {
+        let unique = self.next_unique;
+        self.next_unique += 1;
+
+        let (uid, gid, pid) = current_ids();
+        let len = size_of::<FuseInHeader>() + args.iter().map(|arg| arg.len()).sum::<usize>();
+        let header = FuseInHeader {
+            len: len as u32,
+            opcode: opcode as u32,
+            unique,
+            nodeid,
+            uid,
+            gid,
+            pid,
+            padding: 0,
+        };
+
+        let mut request = Vec::with_capacity(len);
+        request.extend_from_slice(header.as_bytes());
+        for arg in args {
+            request.extend_from_slice(arg);
+        }
+        self.pending.push_back((unique, request));
+
+        unique
+    }
+
+    fn take_reply(&mut self, unique: u64) -> Option<Result<Vec<u8>>> {
+        if !matches!(self.replies.get(&unique), Some(Some(_))) {
+            return None;
+        }
+        self.replies.remove(&unique).flatten()
+    }
+
+    /// Completes the request with the reply, returning `false` if the request is not found.
+    fn complete(&mut self, unique: u64, reply: Result<Vec<u8>>) -> bool {
+        if matches!(self.init, InitState::Sent(init_unique) if init_unique == unique) {
+            self.replies.remove(&unique);
+            self.init = match reply.and_then(|payload| parse_init_out(&payload)) {
+                Ok(info) => InitState::Done(info),
+                Err(err) => {
+                    warn!("the FUSE daemon fails to initialize: {:?}", err);
+                    InitState::Failed
+                }
+            };
+            return true;
+        }
+
+        if self.background.remove(&unique) {
+            return true;
+        }
+
+        match self.replies.get_mut(&unique) {
+            Some(slot) if slot.is_none() => {
+                *slot = Some(reply);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_init_out(payload: &[u8]) -> Result<FuseInitInfo> {
+    // Old daemons reply with a shorter structure, where the missing fields are zeros.
+    let mut init_out = FuseInitOut::new_zeroed();
+    let len = payload.len().min(size_of::<FuseInitOut>());
+    init_out.as_bytes_mut()[..len].copy_from_slice(&payload[..len]);
+
+    if len < 8 || init_out.major != FUSE_KERNEL_VERSION {
+        return_errno_with_message!(Errno::EPROTO, "the FUSE protocol version is unsupported");
+    }
+
+    let max_write = if init_out.minor >= 5 {
+        init_out.max_write.clamp(PAGE_SIZE as u32, MAX_IO_SIZE)
+    } else {
+        PAGE_SIZE as u32
+    };
+    Ok(FuseInitInfo {
+        flags: InitFlags::from_bits_truncate(init_out.flags) & SUPPORTED_INIT_FLAGS,
+        max_write,
+    })
+}
+
+/// Returns the IDs of the current thread, which are reported to the daemon.
+fn current_ids() -> (u32, u32, u32) {
+    let Some(task) = Task::current() else {
+        return (0, 0, 0);
+    };
+    let Some(posix_thread) = task.as_posix_thread() else {
+        return (0, 0, 0);
+    };
+
+    let credentials = posix_thread.credentials();
+    (
+        credentials.fsuid().into(),
+        credentials.fsgid().into(),
+        posix_thread.process().pid(),
+    )
+}
+
+impl Pollable for FuseConn {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileIo for FuseConn {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        if writer.avail() < FUSE_MIN_READ_BUFFER {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for FUSE requests");
+        }
+
+        self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let reply = reader.collect()?;
+        self.push_reply(&reply)?;
+        Ok(len)
+    }
+}
+
+/// The file opened from `/dev/fuse`.
+///
+/// Like Linux, the connection is aborted when the file is closed, which makes the mount
+/// unusable but does not unmount it.
+pub struct FuseDevFile {
+    conn: Arc<FuseConn>,
+}
+
+impl FuseDevFile {
+    pub fn new() -> Self {
+        Self {
+            conn: FuseConn::new(),
+        }
+    }
+
+    pub fn conn(&self) -> &Arc<FuseConn> {
+        &self.conn
+    }
+}
+
+impl Drop for FuseDevFile {
+    fn drop(&mut self) {
+        self.conn.abort();
+    }
+}
+
+impl Pollable for FuseDevFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.conn.poll(mask, poller)
+    }
+}
+
+impl FileIo for FuseDevFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.conn.read(writer)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        self.conn.write(reader)
+    }
+}
@@ -0,0 +1,1093 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use ostd::task::Task;
+
+use super::{
+    abi::{
+        dirent_size, parse, FuseAttr, FuseAttrOut, FuseCreateIn, FuseDirent, FuseEntryOut,
+        FuseForgetIn, FuseFsyncIn, FuseGetattrIn, FuseLinkIn, FuseMkdirIn, FuseMknodIn, FuseOpcode,
+        FuseOpenIn, FuseOpenOut, FuseReadIn, FuseReleaseIn, FuseRenameIn, FuseSetattrIn,
+        FuseStatfsOut, FuseWriteIn, FuseWriteOut, InitFlags, SetattrValid, FUSE_FSYNC_FDATASYNC,
+        FUSE_NAME_MAX, FUSE_ROOT_ID, FUSE_SUPER_MAGIC,
+    },
+    conn::{FuseConn, MAX_IO_SIZE},
+};
+use crate::{
+    fs::{
+        device::Device,
+        file_table::FileDesc,
+        utils::{
+            AccessMode, CreationFlags, DirentVisitor, FileSystem, FsFlags, Inode, InodeMode,
+            InodeType, Metadata, MknodType, Permission, SuperBlock, NAME_MAX,
+        },
+    },
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Gid, Uid},
+    time::clocks::MonotonicCoarseClock,
+};
+
+/// The block size reported if the daemon does not specify it.
+const BLOCK_SIZE: usize = 4096;
+
+/// The mount options of FUSE, which are usually generated by `fusermount`.
+#[derive(Debug, Clone)]
+pub struct FuseMountOptions {
+    /// The file descriptor of the opened `/dev/fuse`, which is absent for virtio-fs.
+    fd: Option<FileDesc>,
+    /// The file type and the permissions of the root directory.
+    root_mode: u32,
+    /// The owner of the mount.
+    user_id: Uid,
+    /// The group of the owner of the mount.
+    group_id: Gid,
+    /// Whether the permissions are checked by the kernel instead of the daemon.
+    default_permissions: bool,
+    /// Whether the users other than the owner can access the files.
+    allow_other: bool,
+    /// The maximum size of the data in a read request.
+    max_read: u32,
+}
+
+impl FuseMountOptions {
+    /// Parses the options from the mount data, e.g., `fd=3,rootmode=40000,user_id=0,group_id=0`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut fd = None;
+        let mut root_mode = None;
+        let mut user_id = None;
+        let mut group_id = None;
+        let mut default_permissions = false;
+        let mut allow_other = false;
+        let mut max_read = MAX_IO_SIZE;
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let parse_num = |radix| {
+                u32::from_str_radix(value, radix).map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the FUSE mount option is invalid")
+                })
+            };
+
+            match key {
+                "fd" => fd = Some(parse_num(10)? as FileDesc),
+                "rootmode" => root_mode = Some(parse_num(8)?),
+                "user_id" => user_id = Some(Uid::new(parse_num(10)?)),
+                "group_id" => group_id = Some(Gid::new(parse_num(10)?)),
+                "default_permissions" => default_permissions = true,
+                "allow_other" => allow_other = true,
+                "max_read" => max_read = parse_num(10)?.clamp(PAGE_SIZE as u32, MAX_IO_SIZE),
+                // The subtype is only shown in the mount table.
+                "subtype" => (),
+                _ => return_errno_with_message!(Errno::EINVAL, "the FUSE mount option is unknown"),
+            }
+        }
+
+        let (Some(fd), Some(root_mode), Some(user_id), Some(group_id)) =
+            (fd, root_mode, user_id, group_id)
+        else {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the FUSE mount options fd, rootmode, user_id, and group_id are required"
+            );
+        };
+        if InodeType::from_raw_mode(root_mode as u16)? != InodeType::Dir {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE root must be a directory");
+        }
+
+        Ok(Self {
+            fd: Some(fd),
+            root_mode,
+            user_id,
+            group_id,
+            default_permissions,
+            allow_other,
+            max_read,
+        })
+    }
+
+    /// Parses the options of virtio-fs from the mount data, e.g., `dax=never`.
+    ///
+    /// Since the daemon runs on the host, the files are accessible to all the users and the
+    /// permissions are checked by the kernel.
+    pub fn parse_virtiofs(data: &str) -> Result<Self> {
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            match option {
+                "dax=never" => (),
+                "dax" | "dax=always" | "dax=inode" => {
+                    return_errno_with_message!(Errno::EINVAL, "the DAX window is not supported")
+                }
+                _ => return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the virtio-fs mount option is unknown"
+                ),
+            }
+        }
+
+        Ok(Self {
+            fd: None,
+            root_mode: InodeType::Dir as u32 | 0o755,
+            user_id: Uid::new_root(),
+            group_id: Gid::new_root(),
+            default_permissions: true,
+            allow_other: true,
+            max_read: MAX_IO_SIZE,
+        })
+    }
+
+    /// Returns the file descriptor of the opened `/dev/fuse`.
+    pub fn fd(&self) -> Option<FileDesc> {
+        self.fd
+    }
+}
+
+bitflags! {
+    /// The requests that the daemon does not implement, which are not sent again.
+    struct Unimplemented: u32 {
+        const OPEN        = 1 << 0;
+        const OPENDIR     = 1 << 1;
+        const CREATE      = 1 << 2;
+        const FSYNC       = 1 << 3;
+        const READDIRPLUS = 1 << 4;
+    }
+}
+
+/// A file system that is served by a FUSE daemon.
+pub struct FuseFS {
+    root: Arc<FuseInode>,
+    conn: Arc<FuseConn>,
+    options: FuseMountOptions,
+    /// The inodes that are alive, indexed by the node IDs.
+    inodes: Mutex<BTreeMap<u64, Weak<FuseInode>>>,
+    unimplemented: AtomicU32,
+}
+
+impl FuseFS {
+    /// Creates a file system over the connection.
+    ///
+    /// The `FUSE_INIT` request is sent, but the reply is not waited for.
+    pub fn new(conn: Arc<FuseConn>, options: FuseMountOptions) -> Result<Arc<Self>> {
+        conn.start_init()?;
+
+        // The root attributes are fetched from the daemon when they are used.
+        let root_attr = FuseAttr {
+            ino: FUSE_ROOT_ID,
+            mode: options.root_mode,
+            nlink: 2,
+            uid: options.user_id.into(),
+            gid: options.group_id.into(),
+            ..Default::default()
+        };
+
+        Ok(Arc::new_cyclic(|weak_fs| Self {
+            root: FuseInode::new(
+                FUSE_ROOT_ID,
+                &root_attr,
+                Duration::ZERO,
+                conn.clone(),
+                weak_fs.clone(),
+            ),
+            conn,
+            options,
+            inodes: Mutex::new(BTreeMap::new()),
+            unimplemented: AtomicU32::new(0),
+        }))
+    }
+
+    /// Returns the inode of the entry in the reply, which counts as a lookup of the node.
+    fn instantiate(&self, entry_out: &FuseEntryOut) -> Result<Arc<FuseInode>> {
+        if entry_out.nodeid == 0 {
+            return_errno_with_message!(Errno::ENOENT, "the FUSE entry does not exist");
+        }
+
+        let attr_valid = valid_duration(entry_out.attr_valid, entry_out.attr_valid_nsec);
+        // Like Linux, the lookups of the root are not counted.
+        if entry_out.nodeid == FUSE_ROOT_ID {
+            self.root.update_attr(&entry_out.attr, attr_valid);
+            return Ok(self.root.clone());
+        }
+
+        let mut inodes = self.inodes.lock();
+        if let Some(inode) = inodes.get(&entry_out.nodeid).and_then(Weak::upgrade) {
+            inode.nlookup.fetch_add(1, Ordering::Relaxed);
+            drop(inodes);
+            inode.update_attr(&entry_out.attr, attr_valid);
+            return Ok(inode);
+        }
+
+        let inode = FuseInode::new(
+            entry_out.nodeid,
+            &entry_out.attr,
+            attr_valid,
+            self.conn.clone(),
+            self.root.fs.clone(),
+        );
+        inode.nlookup.store(1, Ordering::Relaxed);
+        inodes.insert(entry_out.nodeid, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    fn statfs(&self) -> Result<SuperBlock> {
+        let reply = self.conn.request(FuseOpcode::Statfs, FUSE_ROOT_ID, &[])?;
+        let st = parse::<FuseStatfsOut>(&reply)?.st;
+
+        let mut sb = SuperBlock::new(FUSE_SUPER_MAGIC, st.bsize as usize, st.namelen as usize);
+        sb.blocks = st.blocks as usize;
+        sb.bfree = st.bfree as usize;
+        sb.bavail = st.bavail as usize;
+        sb.files = st.files as usize;
+        sb.ffree = st.ffree as usize;
+        if st.frsize != 0 {
+            sb.frsize = st.frsize as usize;
+        }
+        Ok(sb)
+    }
+
+    fn is_unimplemented(&self, request: Unimplemented) -> bool {
+        Unimplemented::from_bits_truncate(self.unimplemented.load(Ordering::Relaxed))
+            .contains(request)
+    }
+
+    fn set_unimplemented(&self, request: Unimplemented) {
+        self.unimplemented
+            .fetch_or(request.bits(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for FuseFS {
+    fn drop(&mut self) {
+        // Like Linux, the daemon is notified of the unmount by the aborted connection.
+        self.conn.abort();
+    }
+}
+
+impl FileSystem for FuseFS {
+    fn sync(&self) -> Result<()> {
+        // The data is written to the daemon synchronously.
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.statfs()
+            .unwrap_or_else(|_| SuperBlock::new(FUSE_SUPER_MAGIC, BLOCK_SIZE, NAME_MAX))
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+/// An inode of a node in a FUSE file system.
+pub struct FuseInode {
+    nodeid: u64,
+    type_: InodeType,
+    /// The number of the lookups, which are forgotten when the inode is dropped.
+    nlookup: AtomicU64,
+    attr: SpinLock<CachedAttr>,
+    handles: Mutex<FileHandles>,
+    dir_cursor: Mutex<DirCursor>,
+    conn: Arc<FuseConn>,
+    fs: Weak<FuseFS>,
+}
+
+struct CachedAttr {
+    attr: FuseAttr,
+    /// The time after which the attributes should be fetched again.
+    expiry: Duration,
+}
+
+/// The file handles that are opened on demand.
+///
+/// Since there are no per-file operations in the VFS, the handles are shared by the opened
+/// files of the inode, and they are released when the inode is dropped.
+#[derive(Default)]
+struct FileHandles {
+    read: Option<u64>,
+    write: Option<u64>,
+}
+
+/// The position after the last `readdir_at`.
+///
+/// The offsets in the FUSE directories are cookies that are chosen by the daemon, so the cookie
+/// of the next entry is remembered to continue reading the directory.
+#[derive(Default)]
+struct DirCursor {
+    offset: usize,
+    cookie: u64,
+}
+
+impl FuseInode {
+    fn new(
+        nodeid: u64,
+        attr: &FuseAttr,
+        attr_valid: Duration,
+        conn: Arc<FuseConn>,
+        fs: Weak<FuseFS>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            nodeid,
+            type_: InodeType::from_raw_mode(attr.mode as u16).unwrap_or(InodeType::File),
+            nlookup: AtomicU64::new(0),
+            attr: SpinLock::new(CachedAttr {
+                attr: *attr,
+                expiry: now().saturating_add(attr_valid),
+            }),
+            handles: Mutex::new(FileHandles::default()),
+            dir_cursor: Mutex::new(DirCursor::default()),
+            conn,
+            fs,
+        })
+    }
+
+    fn fs(&self) -> Arc<FuseFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn request(&self, opcode: FuseOpcode, args: &[&[u8]]) -> Result<Vec<u8>> {
+        self.conn.request(opcode, self.nodeid, args)
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the FUSE inode is not a directory");
+        }
+        Ok(())
+    }
+
+    fn update_attr(&self, attr: &FuseAttr, attr_valid: Duration) {
+        *self.attr.lock() = CachedAttr {
+            attr: *attr,
+            expiry: now().saturating_add(attr_valid),
+        };
+    }
+
+    fn invalidate_attr(&self) {
+        self.attr.lock().expiry = Duration::ZERO;
+    }
+
+    fn getattr(&self) -> Result<FuseAttr> {
+        {
+            let cached = self.attr.lock();
+            if now() < cached.expiry {
+                return Ok(cached.attr);
+            }
+        }
+
+        let getattr_in = FuseGetattrIn::default();
+        let reply = self.request(FuseOpcode::Getattr, &[getattr_in.as_bytes()])?;
+        let attr_out = parse::<FuseAttrOut>(&reply)?;
+        self.update_attr(
+            &attr_out.attr,
+            valid_duration(attr_out.attr_valid, attr_out.attr_valid_nsec),
+        );
+        Ok(attr_out.attr)
+    }
+
+    /// Returns the attributes, which are the cached ones if the daemon fails to reply.
+    fn attr(&self) -> FuseAttr {
+        self.getattr().unwrap_or_else(|err| {
+            debug!("failed to get the FUSE attributes: {:?}", err);
+            self.attr.lock().attr
+        })
+    }
+
+    fn setattr(&self, setattr_in: FuseSetattrIn) -> Result<()> {
+        let reply = self.request(FuseOpcode::Setattr, &[setattr_in.as_bytes()])?;
+        let attr_out = parse::<FuseAttrOut>(&reply)?;
+        self.update_attr(
+            &attr_out.attr,
+            valid_duration(attr_out.attr_valid, attr_out.attr_valid_nsec),
+        );
+        Ok(())
+    }
+
+    fn set_time(&self, valid: SetattrValid, time: Duration) {
+        let mut setattr_in = FuseSetattrIn {
+            valid: valid.bits(),
+            ..Default::default()
+        };
+        if valid == SetattrValid::ATIME {
+            setattr_in.atime = time.as_secs();
+            setattr_in.atimensec = time.subsec_nanos();
+        } else {
+            setattr_in.mtime = time.as_secs();
+            setattr_in.mtimensec = time.subsec_nanos();
+        }
+
+        if let Err(err) = self.setattr(setattr_in) {
+            debug!("failed to set the FUSE times: {:?}", err);
+        }
+    }
+
+    /// Opens the node, returning `None` if the daemon does not implement the opens.
+    fn open(&self, opcode: FuseOpcode, flags: u32) -> Result<Option<u64>> {
+        let fs = self.fs();
+        let unimplemented = if opcode == FuseOpcode::Opendir {
+            Unimplemented::OPENDIR
+        } else {
+            Unimplemented::OPEN
+        };
+        if fs.is_unimplemented(unimplemented) {
+            return Ok(None);
+        }
+
+        let open_in = FuseOpenIn { flags, unused: 0 };
+        match self.request(opcode, &[open_in.as_bytes()]) {
+            Ok(reply) => Ok(Some(parse::<FuseOpenOut>(&reply)?.fh)),
+            // Like Linux, the daemon that does not implement the opens uses zero handles.
+            Err(err) if err.error() == Errno::ENOSYS => {
+                fs.set_unimplemented(unimplemented);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn release(&self, opcode: FuseOpcode, fh: u64, flags: u32) {
+        let release_in = FuseReleaseIn {
+            fh,
+            flags,
+            ..Default::default()
+        };
+        self.conn
+            .send_background(opcode, self.nodeid, &[release_in.as_bytes()]);
+    }
+
+    /// Returns the file handle for reading or writing, which is opened if necessary.
+    fn file_handle(&self, is_write: bool) -> Result<u64> {
+        let mut handles = self.handles.lock();
+        let handle = if is_write {
+            &mut handles.write
+        } else {
+            &mut handles.read
+        };
+        if let Some(fh) = *handle {
+            return Ok(fh);
+        }
+
+        let flags = if is_write {
+            AccessMode::O_WRONLY
+        } else {
+            AccessMode::O_RDONLY
+        };
+        let Some(fh) = self.open(FuseOpcode::Open, flags as u32)? else {
+            return Ok(0);
+        };
+        *handle = Some(fh);
+        Ok(fh)
+    }
+
+    fn set_write_handle(&self, fh: u64) {
+        let mut handles = self.handles.lock();
+        if handles.write.is_none() {
+            handles.write = Some(fh);
+            return;
+        }
+        drop(handles);
+
+        self.release(FuseOpcode::Release, fh, AccessMode::O_WRONLY as u32);
+    }
+
+    /// Creates a node with a request that replies with the entry.
+    fn new_entry(&self, opcode: FuseOpcode, args: &[&[u8]]) -> Result<Arc<FuseInode>> {
+        let reply = self.request(opcode, args)?;
+        let entry_out = parse::<FuseEntryOut>(&reply)?;
+        self.invalidate_attr();
+        self.fs().instantiate(&entry_out)
+    }
+
+    fn create_file(&self, name: &[u8], mode: u32) -> Result<Arc<FuseInode>> {
+        let fs = self.fs();
+
+        if !fs.is_unimplemented(Unimplemented::CREATE) {
+            let create_in = FuseCreateIn {
+                flags: AccessMode::O_WRONLY as u32
+                    | (CreationFlags::O_CREAT | CreationFlags::O_EXCL).bits(),
+                mode,
+                ..Default::default()
+            };
+            match self.request(FuseOpcode::Create, &[create_in.as_bytes(), name]) {
+                Ok(reply) => {
+                    let entry_out = parse::<FuseEntryOut>(&reply)?;
+                    let open_out = parse::<FuseOpenOut>(
+                        reply.get(size_of::<FuseEntryOut>()..).unwrap_or(&[]),
+                    )?;
+                    self.invalidate_attr();
+                    let inode = fs.instantiate(&entry_out)?;
+                    inode.set_write_handle(open_out.fh);
+                    return Ok(inode);
+                }
+                Err(err) if err.error() == Errno::ENOSYS => {
+                    fs.set_unimplemented(Unimplemented::CREATE);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mknod_in = FuseMknodIn {
+            mode,
+            ..Default::default()
+        };
+        self.new_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), name])
+    }
+
+    /// Casts the inode to a `FuseInode` in the same file system.
+    fn same_fs_inode<'a>(&self, inode: &'a Arc<dyn Inode>) -> Result<&'a FuseInode> {
+        inode
+            .downcast_ref::<FuseInode>()
+            .filter(|inode| Weak::ptr_eq(&inode.fs, &self.fs))
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not same fs"))
+    }
+
+    fn fsync(&self, is_datasync: bool) -> Result<()> {
+        let Some(fh) = self.handles.lock().write else {
+            return Ok(());
+        };
+        let fs = self.fs();
+        if fs.is_unimplemented(Unimplemented::FSYNC) {
+            return Ok(());
+        }
+
+        let fsync_in = FuseFsyncIn {
+            fh,
+            fsync_flags: if is_datasync { FUSE_FSYNC_FDATASYNC } else { 0 },
+            padding: 0,
+        };
+        match self.request(FuseOpcode::Fsync, &[fsync_in.as_bytes()]) {
+            Ok(_) => Ok(()),
+            Err(err) if err.error() == Errno::ENOSYS => {
+                fs.set_unimplemented(Unimplemented::FSYNC);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn readdir_with_handle(
+        &self,
+        fh: u64,
+        offset: usize,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<usize> {
+        let fs = self.fs();
+        let is_readdirplus_supported = self
+            .conn
+            .wait_init()?
+            .flags
+            .contains(InitFlags::DO_READDIRPLUS);
+
+        let mut cursor = self.dir_cursor.lock();
+        // The directory is read from the start unless the last read is continued.
+        let (mut index, mut cookie) = if offset != 0 && cursor.offset == offset {
+            (cursor.offset, cursor.cookie)
+        } else {
+            (0, 0)
+        };
+        let mut count = 0;
+
+        'read: loop {
+            let is_plus =
+                is_readdirplus_supported && !fs.is_unimplemented(Unimplemented::READDIRPLUS);
+            let opcode = if is_plus {
+                FuseOpcode::Readdirplus
+            } else {
+                FuseOpcode::Readdir
+            };
+            let read_in = FuseReadIn {
+                fh,
+                offset: cookie,
+                size: PAGE_SIZE as u32,
+                ..Default::default()
+            };
+            let reply = match self.request(opcode, &[read_in.as_bytes()]) {
+                Err(err) if is_plus && err.error() == Errno::ENOSYS => {
+                    fs.set_unimplemented(Unimplemented::READDIRPLUS);
+                    continue;
+                }
+                result => result?,
+            };
+            if reply.is_empty() {
+                break;
+            }
+
+            let entries = parse_dir_entries(&reply, is_plus)?;
+            // Each entry with a node ID counts as a lookup, except for "." and "..".
+            for (entry_out, _, name) in entries.iter() {
+                if let Some(entry_out) = entry_out.filter(|entry_out| {
+                    entry_out.nodeid != 0 && name.as_str() != "." && name.as_str() != ".."
+                }) {
+                    let _ = fs.instantiate(&entry_out);
+                }
+            }
+
+            for (_, dirent, name) in entries.iter() {
+                if index >= offset {
+                    let type_ = InodeType::from_raw_mode((dirent.type_ << 12) as u16)
+                        .unwrap_or(InodeType::File);
+                    if let Err(err) = visitor.visit(name, dirent.ino, type_, index) {
+                        if count == 0 {
+                            return Err(err);
+                        }
+                        break 'read;
+                    }
+                    count += 1;
+                }
+                index += 1;
+                cookie = dirent.off;
+            }
+        }
+
+        *cursor = DirCursor {
+            offset: offset + count,
+            cookie,
+        };
+        Ok(count)
+    }
+}
+
+impl Drop for FuseInode {
+    fn drop(&mut self) {
+        let handles = core::mem::take(&mut *self.handles.lock());
+        if let Some(fh) = handles.read {
+            self.release(FuseOpcode::Release, fh, AccessMode::O_RDONLY as u32);
+        }
+        if let Some(fh) = handles.write {
+            self.release(FuseOpcode::Release, fh, AccessMode::O_WRONLY as u32);
+        }
+
+        let nlookup = self.nlookup.load(Ordering::Relaxed);
+        if nlookup > 0 {
+            let forget_in = FuseForgetIn { nlookup };
+            self.conn
+                .send_background(FuseOpcode::Forget, self.nodeid, &[forget_in.as_bytes()]);
+        }
+
+        if let Some(fs) = self.fs.upgrade() {
+            let mut inodes = fs.inodes.lock();
+            // The entry may be replaced by a new inode of the same node.
+            if inodes
+                .get(&self.nodeid)
+                .is_some_and(|inode| core::ptr::eq(inode.as_ptr(), self))
+            {
+                inodes.remove(&self.nodeid);
+            }
+        }
+    }
+}
+
+impl Inode for FuseInode {
+    fn size(&self) -> usize {
+        self.attr().size as usize
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "the FUSE inode is a directory");
+        }
+
+        self.setattr(FuseSetattrIn {
+            valid: SetattrValid::SIZE.bits(),
+            size: new_size as u64,
+            ..Default::default()
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        let attr = self.attr();
+        Metadata {
+            dev: 0,
+            ino: attr.ino,
+            size: attr.size as usize,
+            blk_size: if attr.blksize != 0 {
+                attr.blksize as usize
+            } else {
+                BLOCK_SIZE
+            },
+            blocks: attr.blocks as usize,
+            atime: Duration::new(attr.atime, attr.atimensec),
+            mtime: Duration::new(attr.mtime, attr.mtimensec),
+            ctime: Duration::new(attr.ctime, attr.ctimensec),
+            type_: self.type_,
+            mode: InodeMode::from_bits_truncate(attr.mode as u16),
+            nlinks: attr.nlink as usize,
+            uid: Uid::new(attr.uid),
+            gid: Gid::new(attr.gid),
+            rdev: attr.rdev as u64,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.attr.lock().attr.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(InodeMode::from_bits_truncate(self.getattr()?.mode as u16))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: SetattrValid::MODE.bits(),
+            mode: self.type_ as u32 | mode.bits() as u32,
+            ..Default::default()
+        })
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.getattr()?.uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: SetattrValid::UID.bits(),
+            uid: uid.into(),
+            ..Default::default()
+        })
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.getattr()?.gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: SetattrValid::GID.bits(),
+            gid: gid.into(),
+            ..Default::default()
+        })
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(SetattrValid::ATIME, time);
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(SetattrValid::MTIME, time);
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata().ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {
+        // Like Linux, the change time is maintained by the daemon.
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        if self.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "the FUSE inode is a directory");
+        }
+
+        let fh = self.file_handle(false)?;
+        let max_read = self.fs().options.max_read as usize;
+        let mut read_len = 0;
+        while writer.has_avail() {
+            let size = writer.avail().min(max_read);
+            let read_in = FuseReadIn {
+                fh,
+                offset: (offset + read_len) as u64,
+                size: size as u32,
+                ..Default::default()
+            };
+            let data = self.request(FuseOpcode::Read, &[read_in.as_bytes()])?;
+            if data.len() > size {
+                return_errno_with_message!(Errno::EIO, "the FUSE daemon reads too much data");
+            }
+
+            writer.write_fallible(&mut VmReader::from(data.as_slice()))?;
+            read_len += data.len();
+            if data.len() < size {
+                break;
+            }
+        }
+
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        if self.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "the FUSE inode is a directory");
+        }
+
+        let fh = self.file_handle(true)?;
+        let max_write = self.conn.wait_init()?.max_write as usize;
+        let mut write_len = 0;
+        while reader.has_remain() {
+            let mut data = vec![0; reader.remain().min(max_write)];
+            reader.read_fallible(&mut VmWriter::from(data.as_mut_slice()))?;
+
+            let write_in = FuseWriteIn {
+                fh,
+                offset: (offset + write_len) as u64,
+                size: data.len() as u32,
+                ..Default::default()
+            };
+            let reply = self.request(FuseOpcode::Write, &[write_in.as_bytes(), &data])?;
+            let written = (parse::<FuseWriteOut>(&reply)?.size as usize).min(data.len());
+            write_len += written;
+            if written < data.len() {
+                break;
+            }
+        }
+
+        // The size is updated at once, while the other attributes are fetched again.
+        let mut cached = self.attr.lock();
+        cached.attr.size = cached.attr.size.max((offset + write_len) as u64);
+        cached.expiry = Duration::ZERO;
+        drop(cached);
+
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let name = cstring(name);
+        let mode = type_ as u32 | mode.bits() as u32;
+        let inode = match type_ {
+            InodeType::File => self.create_file(&name, mode)?,
+            InodeType::Dir => {
+                let mkdir_in = FuseMkdirIn { mode, umask: 0 };
+                self.new_entry(FuseOpcode::Mkdir, &[mkdir_in.as_bytes(), &name])?
+            }
+            InodeType::SymLink => {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the FUSE symlinks must be created with the targets"
+                );
+            }
+            _ => {
+                let mknod_in = FuseMknodIn {
+                    mode,
+                    ..Default::default()
+                };
+                self.new_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), &name])?
+            }
+        };
+        Ok(inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let rdev = match &type_ {
+            MknodType::CharDeviceNode(device) | MknodType::BlockDeviceNode(device) => {
+                u64::from(device.id()) as u32
+            }
+            MknodType::NamedPipeNode => 0,
+        };
+        let mknod_in = FuseMknodIn {
+            mode: type_.inode_type() as u32 | mode.bits() as u32,
+            rdev,
+            ..Default::default()
+        };
+        let inode = self.new_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), &cstring(name)])?;
+        Ok(inode)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        if !self.type_.is_device() {
+            return None;
+        }
+        crate::device::get_device(self.attr().rdev as usize).ok()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        let fh = self.open(FuseOpcode::Opendir, AccessMode::O_RDONLY as u32)?;
+        let result = self.readdir_with_handle(fh.unwrap_or(0), offset, visitor);
+        if let Some(fh) = fh {
+            self.release(FuseOpcode::Releasedir, fh, AccessMode::O_RDONLY as u32);
+        }
+        result
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let old = self.same_fs_inode(old)?;
+
+        let link_in = FuseLinkIn {
+            oldnodeid: old.nodeid,
+        };
+        self.new_entry(FuseOpcode::Link, &[link_in.as_bytes(), &cstring(name)])?;
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.request(FuseOpcode::Unlink, &[&cstring(name)])?;
+        self.invalidate_attr();
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.request(FuseOpcode::Rmdir, &[&cstring(name)])?;
+        self.invalidate_attr();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let reply = self.request(FuseOpcode::Lookup, &[&cstring(name)])?;
+        let entry_out = parse::<FuseEntryOut>(&reply)?;
+        let inode = self.fs().instantiate(&entry_out)?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        self.check_dir()?;
+        let target = self.same_fs_inode(target)?;
+        target.check_dir()?;
+
+        let rename_in = FuseRenameIn {
+            newdir: target.nodeid,
+        };
+        self.request(
+            FuseOpcode::Rename,
+            &[rename_in.as_bytes(), &cstring(old_name), &cstring(new_name)],
+        )?;
+        self.invalidate_attr();
+        target.invalidate_attr();
+        Ok(())
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let inode = self.new_entry(FuseOpcode::Symlink, &[&cstring(name), &cstring(target)])?;
+        Ok(inode)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.type_ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE inode is not a symlink");
+        }
+
+        let reply = self.request(FuseOpcode::Readlink, &[])?;
+        Ok(String::from_utf8(reply)?)
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the FUSE symlinks cannot be changed");
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fsync(false)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fsync(true)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        // The nodes can be changed by the daemon without notice, so they are looked up again.
+        false
+    }
+
+    fn check_permission(&self, perm: Permission) -> Result<()> {
+        let fs = self.fs();
+        let Some(credentials) = Task::current().and_then(|task| {
+            task.as_posix_thread()
+                .map(|posix_thread| posix_thread.credentials())
+        }) else {
+            return Ok(());
+        };
+
+        // Like Linux, only the owner of the mount can access the files without `allow_other`.
+        if !fs.options.allow_other
+            && (credentials.fsuid() != fs.options.user_id
+                || credentials.fsgid() != fs.options.group_id)
+        {
+            return_errno_with_message!(Errno::EACCES, "the FUSE mount is private to the owner");
+        }
+
+        // Otherwise, the permissions are checked by the daemon.
+        if fs.options.default_permissions {
+            self.metadata().check_permission(perm)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the directory entries in the reply of `FUSE_READDIR` or `FUSE_READDIRPLUS`.
+fn parse_dir_entries(
+    mut reply: &[u8],
+    is_plus: bool,
+) -> Result<Vec<(Option<FuseEntryOut>, FuseDirent, String)>> {
+    let mut entries = Vec::new();
+
+    while !reply.is_empty() {
+        let entry_out = if is_plus {
+            let entry_out = parse::<FuseEntryOut>(reply)?;
+            reply = &reply[size_of::<FuseEntryOut>()..];
+            Some(entry_out)
+        } else {
+            None
+        };
+
+        let dirent = parse::<FuseDirent>(reply)?;
+        let name_len = dirent.namelen as usize;
+        if name_len == 0
+            || name_len > FUSE_NAME_MAX
+            || reply.len() < size_of::<FuseDirent>() + name_len
+        {
+            return_errno_with_message!(Errno::EIO, "the FUSE directory entry is invalid");
+        }
+        let name = &reply[size_of::<FuseDirent>()..size_of::<FuseDirent>() + name_len];
+        entries.push((entry_out, dirent, String::from_utf8(name.to_vec())?));
+
+        reply = &reply[dirent_size(name_len).min(reply.len())..];
+    }
+
+    Ok(entries)
+}
+
+fn cstring(name: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(name.len() + 1);
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes
+}
+
+fn now() -> Duration {
+    MonotonicCoarseClock::get().read_time()
+}
+
+fn valid_duration(secs: u64, nsecs: u32) -> Duration {
+    Duration::from_secs(secs).saturating_add(Duration::from_nanos(nsecs as u64))
+}
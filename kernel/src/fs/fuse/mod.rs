@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Filesystem in Userspace (FUSE).
+//!
+//! A FUSE daemon (e.g., `sshfs`, or any program built on libfuse) opens `/dev/fuse` and mounts a
+//! `fuse` file system with the file descriptor in the mount options. Then, the operations on
+//! the files are sent to the daemon as requests, which are read from `/dev/fuse`, and the
+//! replies of the daemon are written to `/dev/fuse`.
+//!
+//! The requests can also be carried by a virtio-fs device, whose daemon runs on the host to
+//! share a host directory. It is mounted as a `virtiofs` file system with the tag of the
+//! device as the source, e.g., `mount -t virtiofs myfs /mnt`.
+//!
+//! The features are as follows:
+//! 1. The protocol of version 7.31, including the negotiation with `FUSE_INIT`.
+//! 2. The operations on the files, the directories, and the symlinks, e.g., `FUSE_LOOKUP`,
+//!    `FUSE_GETATTR`, `FUSE_READ`, `FUSE_WRITE`, `FUSE_CREATE`, and `FUSE_RENAME`.
+//! 3. Reading the directories with `FUSE_READDIRPLUS`, and with `FUSE_READDIR` if the daemon
+//!    does not support it.
+//! 4. Counting the lookups of the nodes, which are forgotten with `FUSE_FORGET` when the
+//!    inodes are dropped.
+//! 5. Interrupting the requests when the waiting threads are interrupted by signals.
+//! 6. The mount options of Linux, e.g., `default_permissions` and `allow_other`.
+//!
+//! # Limitation
+//!
+//! Here we summarizes the features that need to be implemented in the future.
+//! 1. Caches the file data in the page cache, which is required by `mmap`.
+//! 2. Opens a file handle for each opened file. Now the handles are shared by the opened files
+//!    of an inode.
+//! 3. Supports the extended attributes, the file locks, and the notifications of the daemon.
+//! 4. Caches the directory entries with the timeouts in the replies.
+//! 5. Maps the DAX window of virtio-fs, which allows the host pages to be accessed directly.
+
+pub use conn::{FuseConn, FuseDevFile};
+pub use fs::{FuseFS, FuseMountOptions};
+pub use virtio::new_virtiofs;
+
+mod abi;
+mod conn;
+mod fs;
+mod virtio;
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use ostd::prelude::*;
+
+    use super::{abi::*, *};
+    use crate::{
+        fs::{
+            inode_handle::FileIo,
+            utils::{FileSystem, Inode, InodeType},
+        },
+        prelude::*,
+        thread::{kernel_thread::ThreadOptions, Thread},
+    };
+
+    const CONTENT: &[u8] = b"hello";
+    const FILE_ID: u64 = 2;
+
+    fn new_fs(dev: &FuseDevFile) -> Arc<FuseFS> {
+        let options = FuseMountOptions::parse("fd=3,rootmode=40000,user_id=0,group_id=0").unwrap();
+        FuseFS::new(dev.conn().clone(), options).unwrap()
+    }
+
+    fn attr_of(nodeid: u64) -> FuseAttr {
+        if nodeid == FUSE_ROOT_ID {
+            FuseAttr {
+                ino: FUSE_ROOT_ID,
+                mode: InodeType::Dir as u32 | 0o755,
+                nlink: 2,
+                ..Default::default()
+            }
+        } else {
+            FuseAttr {
+                ino: nodeid,
+                size: CONTENT.len() as u64,
+                mode: InodeType::File as u32 | 0o644,
+                nlink: 1,
+                ..Default::default()
+            }
+        }
+    }
+
+    fn entry_of(nodeid: u64) -> FuseEntryOut {
+        FuseEntryOut {
+            nodeid,
+            attr: attr_of(nodeid),
+            ..Default::default()
+        }
+    }
+
+    fn reply(dev: &FuseDevFile, unique: u64, error: i32, payload: &[u8]) {
+        let header = FuseOutHeader {
+            len: (size_of::<FuseOutHeader>() + payload.len()) as u32,
+            error,
+            unique,
+        };
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        dev.write(&mut VmReader::from(bytes.as_slice()).to_fallible())
+            .unwrap();
+    }
+
+    /// Serves a directory that contains a file named `hello`, until the connection is aborted.
+    fn serve(dev: &FuseDevFile, forgotten: &AtomicU64) {
+        let mut buf = vec![0u8; FUSE_MIN_READ_BUFFER];
+        loop {
+            let len = match dev.read(&mut VmWriter::from(buf.as_mut_slice()).to_fallible()) {
+                Ok(len) => len,
+                Err(err) => {
+                    assert_eq!(err.error(), Errno::ENODEV);
+                    return;
+                }
+            };
+            let header = FuseInHeader::from_bytes(&buf[..size_of::<FuseInHeader>()]);
+            assert_eq!(header.len as usize, len);
+            let arg = &buf[size_of::<FuseInHeader>()..len];
+
+            let unique = header.unique;
+            match header.opcode {
+                opcode if opcode == FuseOpcode::Init as u32 => {
+                    let init_in = FuseInitIn::from_bytes(&arg[..size_of::<FuseInitIn>()]);
+                    assert_eq!(init_in.major, FUSE_KERNEL_VERSION);
+                    let init_out = FuseInitOut {
+                        major: FUSE_KERNEL_VERSION,
+                        minor: FUSE_KERNEL_MINOR_VERSION,
+                        flags: InitFlags::DO_READDIRPLUS.bits(),
+                        max_write: PAGE_SIZE as u32,
+                        ..Default::default()
+                    };
+                    reply(dev, unique, 0, init_out.as_bytes());
+                }
+                opcode if opcode == FuseOpcode::Lookup as u32 => {
+                    if arg == b"hello\0" {
+                        reply(dev, unique, 0, entry_of(FILE_ID).as_bytes());
+                    } else {
+                        reply(dev, unique, -(Errno::ENOENT as i32), &[]);
+                    }
+                }
+                opcode if opcode == FuseOpcode::Getattr as u32 => {
+                    let attr_out = FuseAttrOut {
+                        attr: attr_of(header.nodeid),
+                        ..Default::default()
+                    };
+                    reply(dev, unique, 0, attr_out.as_bytes());
+                }
+                opcode if opcode == FuseOpcode::Open as u32 => {
+                    // The opens are not needed by this daemon.
+                    reply(dev, unique, -(Errno::ENOSYS as i32), &[]);
+                }
+                opcode if opcode == FuseOpcode::Opendir as u32 => {
+                    let open_out = FuseOpenOut {
+                        fh: 1,
+                        ..Default::default()
+                    };
+                    reply(dev, unique, 0, open_out.as_bytes());
+                }
+                opcode if opcode == FuseOpcode::Read as u32 => {
+                    let read_in = FuseReadIn::from_bytes(&arg[..size_of::<FuseReadIn>()]);
+                    assert_eq!(read_in.fh, 0);
+                    let start = (read_in.offset as usize).min(CONTENT.len());
+                    let end = (start + read_in.size as usize).min(CONTENT.len());
+                    reply(dev, unique, 0, &CONTENT[start..end]);
+                }
+                opcode if opcode == FuseOpcode::Readdirplus as u32 => {
+                    let read_in = FuseReadIn::from_bytes(&arg[..size_of::<FuseReadIn>()]);
+                    assert_eq!(read_in.fh, 1);
+
+                    let mut entries = Vec::new();
+                    let names: [(&[u8], u64); 2] = [(b".", FUSE_ROOT_ID), (b"hello", FILE_ID)];
+                    for (index, (name, nodeid)) in
+                        names.iter().enumerate().skip(read_in.offset as usize)
+                    {
+                        let entry_out = entry_of(*nodeid);
+                        let dirent = FuseDirent {
+                            ino: *nodeid,
+                            off: index as u64 + 1,
+                            namelen: name.len() as u32,
+                            type_: entry_out.attr.mode >> 12,
+                        };
+                        entries.extend_from_slice(entry_out.as_bytes());
+                        entries.extend_from_slice(dirent.as_bytes());
+                        entries.extend_from_slice(name);
+                        entries.resize(
+                            entries.len() - size_of::<FuseDirent>() - name.len()
+                                + dirent_size(name.len()),
+                            0,
+                        );
+                    }
+                    reply(dev, unique, 0, &entries);
+                }
+                opcode if opcode == FuseOpcode::Releasedir as u32 => {
+                    reply(dev, unique, 0, &[]);
+                }
+                opcode if opcode == FuseOpcode::Forget as u32 => {
+                    assert_eq!(header.nodeid, FILE_ID);
+                    let forget_in = FuseForgetIn::from_bytes(&arg[..size_of::<FuseForgetIn>()]);
+                    forgotten.fetch_add(forget_in.nlookup, Ordering::Relaxed);
+                }
+                opcode => panic!("unexpected FUSE opcode {}", opcode),
+            }
+        }
+    }
+
+    #[ktest]
+    fn lookup_read_and_forget() {
+        let dev = Arc::new(FuseDevFile::new());
+        let forgotten = Arc::new(AtomicU64::new(0));
+
+        let daemon = {
+            let dev = dev.clone();
+            let forgotten = forgotten.clone();
+            ThreadOptions::new(move || serve(&dev, &forgotten)).spawn()
+        };
+
+        let fs = new_fs(&dev);
+        let root = fs.root_inode();
+        assert_eq!(root.type_(), InodeType::Dir);
+        assert_eq!(root.metadata().mode.bits(), 0o755);
+
+        let file = root.lookup("hello").unwrap();
+        assert_eq!(file.ino(), FILE_ID);
+        assert_eq!(file.type_(), InodeType::File);
+        assert_eq!(file.size(), CONTENT.len());
+        assert_eq!(root.lookup("missing").unwrap_err().error(), Errno::ENOENT);
+
+        let mut buf = [0u8; 16];
+        let len = file
+            .read_at(0, &mut VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf[..len], CONTENT);
+        let len = file
+            .read_at(2, &mut VmWriter::from(&mut buf[..]).to_fallible())
+            .unwrap();
+        assert_eq!(&buf[..len], &CONTENT[2..]);
+
+        let mut names: Vec<String> = Vec::new();
+        assert_eq!(root.readdir_at(0, &mut names).unwrap(), 2);
+        assert_eq!(names, [".", "hello"]);
+        let mut names: Vec<String> = Vec::new();
+        assert_eq!(root.readdir_at(1, &mut names).unwrap(), 1);
+        assert_eq!(names, ["hello"]);
+
+        // The file is looked up once by `lookup` and twice by `FUSE_READDIRPLUS`.
+        drop(file);
+        while forgotten.load(Ordering::Relaxed) != 3 {
+            Thread::yield_now();
+        }
+
+        drop(root);
+        drop(fs);
+        daemon.join();
+    }
+
+    #[ktest]
+    fn abort_when_dev_closed() {
+        let dev = FuseDevFile::new();
+        let fs = new_fs(&dev);
+        let root = fs.root_inode();
+
+        drop(dev);
+        assert_eq!(root.lookup("hello").unwrap_err().error(), Errno::ENOTCONN);
+    }
+
+    #[ktest]
+    fn parse_mount_options() {
+        let options =
+            FuseMountOptions::parse("fd=5,rootmode=40755,user_id=1000,group_id=100,allow_other")
+                .unwrap();
+        assert_eq!(options.fd(), Some(5));
+        assert!(FuseMountOptions::parse_virtiofs("dax=never").is_ok());
+
+        for data in [
+            "rootmode=40000,user_id=0,group_id=0",
+            "fd=3,rootmode=100644,user_id=0,group_id=0",
+            "fd=3,rootmode=40000,user_id=0,group_id=0,unknown",
+        ] {
+            assert_eq!(
+                FuseMountOptions::parse(data).unwrap_err().error(),
+                Errno::EINVAL
+            );
+        }
+        for data in ["dax", "dax=always", "unknown"] {
+            assert_eq!(
+                FuseMountOptions::parse_virtiofs(data).unwrap_err().error(),
+                Errno::EINVAL
+            );
+        }
+    }
+}
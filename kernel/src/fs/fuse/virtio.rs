@@ -0,0 +1,385 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The transport that carries FUSE requests to a virtio-fs device.
+//!
+//! The daemon (e.g., `virtiofsd`) runs on the host, so there is no `/dev/fuse`. Instead, a
+//! kernel thread takes the requests from the connection, submits them to the device, and
+//! completes them with the replies of the device.
+
+use aster_virtio::{
+    device::filesystem::{device::FileSystemDevice, get_device},
+    queue::QueueError,
+};
+use ostd::sync::WaitQueue;
+
+use super::{
+    abi::{parse, FuseInHeader, FuseOpcode, FuseOutHeader, FuseReadIn, FUSE_KERNEL_MINOR_VERSION},
+    conn::FuseConn,
+    fs::{FuseFS, FuseMountOptions},
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::signal::{PollAdaptor, Pollable},
+    thread::kernel_thread::ThreadOptions,
+};
+
+/// The tags of the devices that are mounted.
+///
+/// Like Linux, a device can only be mounted once at a time, since the daemon serves a single
+/// FUSE session.
+static MOUNTED_TAGS: SpinLock<BTreeSet<String>> = SpinLock::new(BTreeSet::new());
+
+/// The unique ID of `FUSE_DESTROY`, which is never used by the connection.
+const DESTROY_UNIQUE: u64 = 1;
+
+/// Creates a FUSE file system that is served by the virtio-fs device with the tag.
+pub fn new_virtiofs(tag: &str, options: FuseMountOptions) -> Result<Arc<FuseFS>> {
+    let device = get_device(tag)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the virtio-fs tag does not exist"))?;
+    if !MOUNTED_TAGS.lock().insert(tag.to_string()) {
+        return_errno_with_message!(Errno::EBUSY, "the virtio-fs device is already mounted");
+    }
+
+    let conn = FuseConn::new();
+    let fs = match FuseFS::new(conn.clone(), options) {
+        Ok(fs) => fs,
+        Err(err) => {
+            MOUNTED_TAGS.lock().remove(tag);
+            return Err(err);
+        }
+    };
+
+    let transport = VirtioFsTransport {
+        device,
+        conn,
+        wait_queue: Arc::new(WaitQueue::new()),
+    };
+    ThreadOptions::new(move || transport.run()).spawn();
+
+    Ok(fs)
+}
+
+struct VirtioFsTransport {
+    device: Arc<FileSystemDevice>,
+    conn: Arc<FuseConn>,
+    /// The queue that is woken up when there are new requests or new replies.
+    wait_queue: Arc<WaitQueue>,
+}
+
+impl VirtioFsTransport {
+    fn run(self) {
+        let wait_queue = self.wait_queue.clone();
+        self.device.set_callback(Box::new(move || {
+            wait_queue.wake_all();
+        }));
+        let mut poll_adaptor = PollAdaptor::with_observer(QueueWaker(self.wait_queue.clone()));
+        self.conn
+            .poll(IoEvents::IN, Some(poll_adaptor.as_handle_mut()));
+
+        // The request that cannot be submitted because the virtqueue is full.
+        let mut stalled = None;
+
+        loop {
+            while let Some(reply) = self.device.pop_reply() {
+                if let Err(err) = self.conn.push_reply(&reply) {
+                    debug!("the virtio-fs reply is dropped: {:?}", err);
+                }
+            }
+
+            if stalled.is_none() {
+                match self.conn.pop_request(usize::MAX) {
+                    Ok(request) => stalled = request,
+                    // The connection is aborted, i.e., the file system is unmounted.
+                    Err(_) => break,
+                }
+            }
+
+            if let Some((unique, request)) = stalled.take() {
+                match self.submit(&request) {
+                    Ok(()) => continue,
+                    Err(QueueError::BufferTooSmall) => stalled = Some((unique, request)),
+                    Err(err) => {
+                        warn!("failed to submit the virtio-fs request: {:?}", err);
+                        self.conn.fail_request(
+                            unique,
+                            Error::with_message(Errno::EIO, "the virtio-fs request fails"),
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            // Wait until there are new replies, new requests, or free descriptors.
+            let num_submitted = self.device.num_submitted();
+            let is_stalled = stalled.is_some();
+            self.wait_queue.wait_until(|| {
+                let has_progress = if is_stalled {
+                    self.device.num_submitted() < num_submitted
+                } else {
+                    self.conn.poll(IoEvents::IN, None).contains(IoEvents::IN)
+                };
+                (has_progress || self.device.has_replies() || self.conn.is_aborted()).then_some(())
+            });
+        }
+
+        self.shutdown();
+    }
+
+    fn submit(&self, request: &[u8]) -> core::result::Result<(), QueueError> {
+        match reply_buf_len(request) {
+            Some(reply_len) => self.device.submit(request, reply_len),
+            None => Ok(()),
+        }
+    }
+
+    /// Ends the FUSE session after the file system is unmounted.
+    fn shutdown(self) {
+        // Like Linux, the daemon is told that the session ends with `FUSE_DESTROY`.
+        let header = FuseInHeader {
+            len: size_of::<FuseInHeader>() as u32,
+            opcode: FuseOpcode::Destroy as u32,
+            unique: DESTROY_UNIQUE,
+            ..Default::default()
+        };
+        self.wait_queue.wait_until(|| {
+            match self
+                .device
+                .submit(header.as_bytes(), size_of::<FuseOutHeader>() + PAGE_SIZE)
+            {
+                Err(QueueError::BufferTooSmall) => None,
+                _ => Some(()),
+            }
+        });
+
+        // The replies of the old session must not be mistaken for those of the next session.
+        self.wait_queue.wait_until(|| {
+            while self.device.pop_reply().is_some() {}
+            (self.device.num_submitted() == 0).then_some(())
+        });
+        while self.device.pop_reply().is_some() {}
+
+        debug!(
+            "the virtio-fs session of {:?} ends (protocol 7.{})",
+            self.device.tag(),
+            FUSE_KERNEL_MINOR_VERSION
+        );
+        MOUNTED_TAGS.lock().remove(self.device.tag());
+    }
+}
+
+/// Returns the length of the buffer that receives the reply of the request, or `None` if the
+/// request should not be submitted.
+fn reply_buf_len(request: &[u8]) -> Option<usize> {
+    let header = FuseInHeader::from_bytes(&request[..size_of::<FuseInHeader>()]);
+    let reply_len = match header.opcode {
+        // The requests without replies are sent via the high-priority queue.
+        opcode if opcode == FuseOpcode::Forget as u32 => 0,
+        // Like Linux, the interrupts are not supported by virtio-fs, so the requests are
+        // always completed by the device.
+        opcode if opcode == FuseOpcode::Interrupt as u32 => return None,
+        opcode
+            if opcode == FuseOpcode::Read as u32
+                || opcode == FuseOpcode::Readdir as u32
+                || opcode == FuseOpcode::Readdirplus as u32 =>
+        {
+            let read_len = parse::<FuseReadIn>(&request[size_of::<FuseInHeader>()..])
+                .map_or(0, |read_in| read_in.size as usize);
+            size_of::<FuseOutHeader>() + read_len.max(PAGE_SIZE)
+        }
+        _ => size_of::<FuseOutHeader>() + PAGE_SIZE,
+    };
+    Some(reply_len)
+}
+
+/// An observer that wakes up the transport when there are new requests.
+struct QueueWaker(Arc<WaitQueue>);
+
+impl Observer<IoEvents> for QueueWaker {
+    fn on_events(&self, _events: &IoEvents) {
+        self.0.wake_all();
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::{
+        fs::fuse::abi::{FuseInitOut, FUSE_KERNEL_VERSION, FUSE_ROOT_ID},
+        thread::Thread,
+    };
+
+    /// Takes a request from the connection, like the transport does.
+    fn pop_request(conn: &FuseConn) -> (u64, Vec<u8>) {
+        loop {
+            if let Some(request) = conn.pop_request(usize::MAX).unwrap() {
+                return request;
+            }
+            Thread::yield_now();
+        }
+    }
+
+    fn new_reply(unique: u64, error: i32, payload: &[u8]) -> Vec<u8> {
+        let header = FuseOutHeader {
+            len: (size_of::<FuseOutHeader>() + payload.len()) as u32,
+            error,
+            unique,
+        };
+        let mut reply = header.as_bytes().to_vec();
+        reply.extend_from_slice(payload);
+        reply
+    }
+
+    fn new_initialized_conn() -> Arc<FuseConn> {
+        let conn = FuseConn::new();
+        conn.start_init().unwrap();
+
+        let (unique, _) = pop_request(&conn);
+        let init_out = FuseInitOut {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            ..Default::default()
+        };
+        conn.push_reply(&new_reply(unique, 0, init_out.as_bytes()))
+            .unwrap();
+        conn.wait_init().unwrap();
+
+        conn
+    }
+
+    /// Sends a `FUSE_GETATTR` request in another thread, and completes it with the replies
+    /// returned by `replies`, which are pushed in order.
+    ///
+    /// Returns the results of pushing the replies and the result of the request.
+    fn request_with_replies(
+        replies: impl FnOnce(u64) -> Vec<Vec<u8>>,
+    ) -> (Vec<Result<()>>, Result<Vec<u8>>) {
+        let conn = new_initialized_conn();
+        let result = Arc::new(SpinLock::new(None));
+
+        let requester = {
+            let conn = conn.clone();
+            let result = result.clone();
+            ThreadOptions::new(move || {
+                *result.lock() = Some(conn.request(FuseOpcode::Getattr, FUSE_ROOT_ID, &[]));
+            })
+            .spawn()
+        };
+
+        let (unique, _) = pop_request(&conn);
+        let push_results = replies(unique)
+            .iter()
+            .map(|reply| conn.push_reply(reply))
+            .collect();
+        requester.join();
+
+        let result = result.lock().take().unwrap();
+        (push_results, result)
+    }
+
+    #[ktest]
+    fn reply_payload() {
+        let (push_results, result) =
+            request_with_replies(|unique| vec![new_reply(unique, 0, b"payload")]);
+        assert!(push_results[0].is_ok());
+        assert_eq!(result.unwrap(), b"payload");
+    }
+
+    #[ktest]
+    fn reply_errors() {
+        let (push_results, result) =
+            request_with_replies(|unique| vec![new_reply(unique, -(Errno::ENOENT as i32), &[])]);
+        assert!(push_results[0].is_ok());
+        assert_eq!(result.unwrap_err().error(), Errno::ENOENT);
+
+        // The errors that are not known are mapped to `EIO`.
+        let (push_results, result) =
+            request_with_replies(|unique| vec![new_reply(unique, -300, &[])]);
+        assert!(push_results[0].is_ok());
+        assert_eq!(result.unwrap_err().error(), Errno::EIO);
+    }
+
+    #[ktest]
+    fn malformed_replies() {
+        let (push_results, result) = request_with_replies(|unique| {
+            let mut bad_len = new_reply(unique, 0, b"payload");
+            bad_len.pop();
+
+            vec![
+                // Too short to contain the header.
+                new_reply(unique, 0, &[])[..size_of::<FuseOutHeader>() - 1].to_vec(),
+                bad_len,
+                // An error with a payload.
+                new_reply(unique, -(Errno::ENOENT as i32), b"payload"),
+                // Errors that are out of range.
+                new_reply(unique, -512, &[]),
+                new_reply(unique, 1, &[]),
+                // A request that does not exist.
+                new_reply(unique + 1, 0, &[]),
+                // A notification, which is ignored.
+                new_reply(0, 0, b"notification"),
+                new_reply(unique, 0, b"payload"),
+            ]
+        });
+
+        let errors: Vec<_> = push_results
+            .iter()
+            .map(|result| result.as_ref().err().map(Error::error))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                Some(Errno::EINVAL),
+                Some(Errno::EINVAL),
+                Some(Errno::EINVAL),
+                Some(Errno::EINVAL),
+                Some(Errno::EINVAL),
+                Some(Errno::ENOENT),
+                None,
+                None,
+            ]
+        );
+        // The malformed replies do not complete the request.
+        assert_eq!(result.unwrap(), b"payload");
+    }
+
+    #[ktest]
+    fn reply_buffer_len() {
+        let request = |opcode: FuseOpcode, arg: &[u8]| {
+            let header = FuseInHeader {
+                len: (size_of::<FuseInHeader>() + arg.len()) as u32,
+                opcode: opcode as u32,
+                unique: 2,
+                ..Default::default()
+            };
+            let mut request = header.as_bytes().to_vec();
+            request.extend_from_slice(arg);
+            request
+        };
+        let read_in = |size: usize| FuseReadIn {
+            size: size as u32,
+            ..Default::default()
+        };
+        let header_len = size_of::<FuseOutHeader>();
+
+        assert_eq!(
+            reply_buf_len(&request(FuseOpcode::Getattr, &[])),
+            Some(header_len + PAGE_SIZE)
+        );
+        assert_eq!(
+            reply_buf_len(&request(
+                FuseOpcode::Read,
+                read_in(PAGE_SIZE * 4).as_bytes()
+            )),
+            Some(header_len + PAGE_SIZE * 4)
+        );
+        assert_eq!(
+            reply_buf_len(&request(FuseOpcode::Readdirplus, read_in(16).as_bytes())),
+            Some(header_len + PAGE_SIZE)
+        );
+        assert_eq!(reply_buf_len(&request(FuseOpcode::Forget, &[])), Some(0));
+        assert_eq!(reply_buf_len(&request(FuseOpcode::Interrupt, &[])), None);
+    }
+}
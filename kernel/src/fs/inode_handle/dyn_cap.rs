@@ -17,6 +17,28 @@ impl InodeHandle<Rights> {
         dentry: Dentry,
         access_mode: AccessMode,
         status_flags: StatusFlags,
+    ) -> Result<Self> {
+        let is_fsnotify_enabled = !status_flags.contains(StatusFlags::O_PATH);
+        Self::new_inner(dentry, access_mode, status_flags, is_fsnotify_enabled)
+    }
+
+    /// Creates a handle whose operations do not report file system notifications.
+    ///
+    /// The access mode is not checked either, so this is only for the files opened by the
+    /// notification groups, e.g., the files in the fanotify events.
+    pub fn new_without_fsnotify(
+        dentry: Dentry,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+    ) -> Result<Self> {
+        Self::new_inner(dentry, access_mode, status_flags, false)
+    }
+
+    fn new_inner(
+        dentry: Dentry,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+        is_fsnotify_enabled: bool,
     ) -> Result<Self> {
         let inode = dentry.inode();
         if inode.type_() == InodeType::Dir && access_mode.is_writable() {
@@ -26,15 +48,16 @@ impl InodeHandle<Rights> {
         let file_io = if let Some(device) = inode.as_device() {
             device.open()?
         } else {
-            None
+            inode.open()?
         };
 
         let inner = Arc::new(InodeHandle_ {
             dentry,
             file_io,
-            offset: Mutex::new(0),
+            offset: PiMutex::new(0),
             access_mode,
             status_flags: AtomicU32::new(status_flags.bits()),
+            is_fsnotify_enabled,
         });
         Ok(Self(inner, Rights::from(access_mode)))
     }
@@ -84,35 +107,61 @@ impl FileLike for InodeHandle<Rights> {
         if !self.1.contains(Rights::READ) {
             return_errno_with_message!(Errno::EBADF, "file is not readable");
         }
-        self.0.read(writer)
+        if self.0.is_fsnotify_enabled {
+            notify::on_access_perm(&self.0.dentry)?;
+        }
+        let read_len = self.0.read(writer)?;
+        if self.0.is_fsnotify_enabled && read_len > 0 {
+            notify::on_access(&self.0.dentry);
+        }
+        Ok(read_len)
     }
 
     fn write(&self, reader: &mut VmReader) -> Result<usize> {
         if !self.1.contains(Rights::WRITE) {
             return_errno_with_message!(Errno::EBADF, "file is not writable");
         }
-        self.0.write(reader)
+        let write_len = self.0.write(reader)?;
+        if self.0.is_fsnotify_enabled && write_len > 0 {
+            notify::on_modify(&self.0.dentry);
+        }
+        Ok(write_len)
     }
 
     fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
         if !self.1.contains(Rights::READ) {
             return_errno_with_message!(Errno::EBADF, "file is not readable");
         }
-        self.0.read_at(offset, writer)
+        if self.0.is_fsnotify_enabled {
+            notify::on_access_perm(&self.0.dentry)?;
+        }
+        let read_len = self.0.read_at(offset, writer)?;
+        if self.0.is_fsnotify_enabled && read_len > 0 {
+            notify::on_access(&self.0.dentry);
+        }
+        Ok(read_len)
     }
 
     fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
         if !self.1.contains(Rights::WRITE) {
             return_errno_with_message!(Errno::EBADF, "file is not writable");
         }
-        self.0.write_at(offset, reader)
+        let write_len = self.0.write_at(offset, reader)?;
+        if self.0.is_fsnotify_enabled && write_len > 0 {
+            notify::on_modify(&self.0.dentry);
+        }
+        Ok(write_len)
     }
 
     fn resize(&self, new_size: usize) -> Result<()> {
         if !self.1.contains(Rights::WRITE) {
             return_errno_with_message!(Errno::EINVAL, "file is not writable");
         }
-        self.0.resize(new_size)
+        self.0.resize(new_size)?;
+        if self.0.is_fsnotify_enabled {
+            notify::on_modify(&self.0.dentry);
+        }
+        Ok(())
     }
 
     fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
@@ -124,6 +173,10 @@ impl FileLike for InodeHandle<Rights> {
         if !self.1.contains(Rights::WRITE) {
             return_errno_with_message!(Errno::EBADF, "file is not writable");
         }
-        self.0.fallocate(mode, offset, len)
+        self.0.fallocate(mode, offset, len)?;
+        if self.0.is_fsnotify_enabled {
+            notify::on_modify(&self.0.dentry);
+        }
+        Ok(())
     }
 }
@@ -11,16 +11,18 @@ use core::sync::atomic::{AtomicU32, Ordering};
 
 use aster_rights::Rights;
 use inherit_methods_macro::inherit_methods;
+use ostd::sync::PiMutex;
 
 use crate::{
     events::IoEvents,
     fs::{
         file_handle::FileLike,
+        notify,
         path::Dentry,
         utils::{
-            AccessMode, DirentVisitor, FallocMode, FileRange, FlockItem, FlockList, InodeMode,
-            InodeType, IoctlCmd, Metadata, RangeLockItem, RangeLockItemBuilder, RangeLockList,
-            RangeLockType, SeekFrom, StatusFlags, OFFSET_MAX,
+            AccessMode, DirentVisitor, FallocMode, FlockItem, FlockList, InodeFlags, InodeMode,
+            InodeType, IoctlCmd, Metadata, RangeLockItem, RangeLockList, RangeLockOwner,
+            RangeLockType, SeekFrom, StatusFlags,
         },
     },
     prelude::*,
@@ -28,6 +30,7 @@ use crate::{
         signal::{PollHandle, Pollable},
         Gid, Uid,
     },
+    vm::vmo::Vmo,
 };
 
 #[derive(Debug)]
@@ -39,18 +42,23 @@ struct InodeHandle_ {
     /// `file_io` is Some, typical file operations including `read`, `write`, `poll`,
     /// `ioctl` will be provided by `file_io`, instead of `dentry`.
     file_io: Option<Arc<dyn FileIo>>,
-    offset: Mutex<usize>,
+    offset: PiMutex<usize>,
     access_mode: AccessMode,
     status_flags: AtomicU32,
+    /// Whether the operations on the file report file system notifications.
+    is_fsnotify_enabled: bool,
 }
 
 impl InodeHandle_ {
     pub fn read(&self, writer: &mut VmWriter) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            return file_io.read(writer);
-        }
-
-        if !self.dentry.inode().is_seekable() {
+            if !file_io.is_seekable() {
+                if self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+                    return file_io.try_read(writer);
+                }
+                return file_io.read(writer);
+            }
+        } else if !self.dentry.inode().is_seekable() {
             return self.read_at(0, writer);
         }
 
@@ -64,10 +72,10 @@ impl InodeHandle_ {
 
     pub fn write(&self, reader: &mut VmReader) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            return file_io.write(reader);
-        }
-
-        if !self.dentry.inode().is_seekable() {
+            if !file_io.is_seekable() {
+                return file_io.write(reader);
+            }
+        } else if !self.dentry.inode().is_seekable() {
             return self.write_at(0, reader);
         }
 
@@ -85,19 +93,23 @@ impl InodeHandle_ {
 
     pub fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            todo!("support read_at for FileIo");
+            return file_io.read_at(offset, writer);
         }
 
-        if self.status_flags().contains(StatusFlags::O_DIRECT) {
-            self.dentry.inode().read_direct_at(offset, writer)
+        let read_len = if self.status_flags().contains(StatusFlags::O_DIRECT) {
+            self.dentry.inode().read_direct_at(offset, writer)?
         } else {
-            self.dentry.inode().read_at(offset, writer)
+            self.dentry.inode().read_at(offset, writer)?
+        };
+        if self.dentry.type_() == InodeType::File {
+            self.touch_atime();
         }
+        Ok(read_len)
     }
 
     pub fn write_at(&self, mut offset: usize, reader: &mut VmReader) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            todo!("support write_at for FileIo");
+            return file_io.write_at(offset, reader);
         }
 
         let status_flags = self.status_flags();
@@ -132,6 +144,8 @@ impl InodeHandle_ {
             SeekFrom::Current(off /* as isize */) => (*offset as isize)
                 .checked_add(off)
                 .ok_or_else(|| Error::with_message(Errno::EOVERFLOW, "file offset overflow"))?,
+            SeekFrom::Data(off /* as usize */) => self.seek_data(off)? as isize,
+            SeekFrom::Hole(off /* as usize */) => self.seek_hole(off)? as isize,
         };
         if new_offset < 0 {
             return_errno_with_message!(Errno::EINVAL, "file offset must not be negative");
@@ -142,6 +156,41 @@ impl InodeHandle_ {
         Ok(new_offset)
     }
 
+    /// Returns the start of the next data region at or after `offset`.
+    fn seek_data(&self, offset: usize) -> Result<usize> {
+        let inode = self.dentry.inode();
+        if offset >= inode.size() {
+            return_errno_with_message!(Errno::ENXIO, "the offset is beyond the end of the file");
+        }
+
+        match inode.next_data_extent(offset)? {
+            Some(extent) => Ok(extent.start.max(offset)),
+            None => return_errno_with_message!(Errno::ENXIO, "there is no data after the offset"),
+        }
+    }
+
+    /// Returns the start of the next hole at or after `offset`.
+    ///
+    /// There is always an implicit hole at the end of the file.
+    fn seek_hole(&self, offset: usize) -> Result<usize> {
+        let inode = self.dentry.inode();
+        let size = inode.size();
+        if offset >= size {
+            return_errno_with_message!(Errno::ENXIO, "the offset is beyond the end of the file");
+        }
+
+        let mut hole = offset;
+        while let Some(extent) = inode.next_data_extent(hole)? {
+            if extent.start > hole {
+                break;
+            }
+            debug_assert!(extent.end > hole);
+            hole = extent.end;
+        }
+
+        Ok(hole.min(size))
+    }
+
     pub fn offset(&self) -> usize {
         let offset = self.offset.lock();
         *offset
@@ -151,6 +200,14 @@ impl InodeHandle_ {
         if self.status_flags().contains(StatusFlags::O_APPEND) {
             return_errno_with_message!(Errno::EPERM, "can not resize append-only file");
         }
+        if self
+            .dentry
+            .inode()
+            .flags()
+            .intersects(InodeFlags::IMMUTABLE | InodeFlags::APPEND)
+        {
+            return_errno_with_message!(Errno::EPERM, "the file is immutable or append-only");
+        }
         self.dentry.resize(new_size)
     }
 
@@ -172,9 +229,16 @@ impl InodeHandle_ {
         let mut offset = self.offset.lock();
         let read_cnt = self.dentry.inode().readdir_at(*offset, visitor)?;
         *offset += read_cnt;
+        self.touch_atime();
         Ok(read_cnt)
     }
 
+    fn touch_atime(&self) {
+        if !self.status_flags().contains(StatusFlags::O_NOATIME) {
+            self.dentry.touch_atime();
+        }
+    }
+
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
         if let Some(ref file_io) = self.file_io {
             return file_io.poll(mask, poller);
@@ -255,17 +319,12 @@ impl InodeHandle_ {
         }
     }
 
-    fn release_range_locks(&self) {
-        if self.dentry.inode().extension().is_none() {
-            return;
+    fn release_range_locks(&self, owner: RangeLockOwner) {
+        if let Some(extension) = self.dentry.inode().extension() {
+            if let Some(range_lock_list) = extension.get::<RangeLockList>() {
+                range_lock_list.release(owner);
+            }
         }
-
-        let range_lock = RangeLockItemBuilder::new()
-            .type_(RangeLockType::Unlock)
-            .range(FileRange::new(0, OFFSET_MAX).unwrap())
-            .build()
-            .unwrap();
-        self.unlock_range_lock(&range_lock);
     }
 
     fn unlock_range_lock(&self, lock: &RangeLockItem) {
@@ -330,6 +389,14 @@ impl InodeHandle_ {
     pub fn set_group(&self, gid: Gid) -> Result<()>;
 }
 
+impl Drop for InodeHandle_ {
+    fn drop(&mut self) {
+        if self.is_fsnotify_enabled {
+            notify::on_close(&self.dentry, self.access_mode.is_writable());
+        }
+    }
+}
+
 impl Debug for InodeHandle_ {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("InodeHandle_")
@@ -355,8 +422,14 @@ impl<R> InodeHandle<R> {
         self.0.set_range_lock(lock, is_nonblocking)
     }
 
-    pub fn release_range_locks(&self) {
-        self.0.release_range_locks()
+    /// Releases all the range locks of the owner on the file.
+    pub fn release_range_locks(&self, owner: RangeLockOwner) {
+        self.0.release_range_locks(owner)
+    }
+
+    /// Returns the owner of the open file description (OFD) locks set via this file.
+    pub fn ofd_lock_owner(&self) -> RangeLockOwner {
+        RangeLockOwner::OpenFile(self as *const Self as usize)
     }
 
     pub fn set_flock(&self, lock: FlockItem, is_nonblocking: bool) -> Result<()> {
@@ -370,21 +443,68 @@ impl<R> InodeHandle<R> {
     pub fn offset(&self) -> usize {
         self.0.offset()
     }
+
+    /// Returns the I/O object provided when the file is opened, if any.
+    pub fn file_io(&self) -> Option<&Arc<dyn FileIo>> {
+        self.0.file_io.as_ref()
+    }
 }
 
 impl<R> Drop for InodeHandle<R> {
     fn drop(&mut self) {
-        self.release_range_locks();
+        // Traditional POSIX locks are released when the file is closed in the file table, while
+        // OFD locks are released when the last reference to the opened file is dropped.
+        self.release_range_locks(self.ofd_lock_owner());
         self.unlock_flock();
     }
 }
 
-pub trait FileIo: Pollable + Send + Sync + 'static {
+pub trait FileIo: Pollable + Send + Sync + Any {
     fn read(&self, writer: &mut VmWriter) -> Result<usize>;
 
+    /// Reads without blocking, which is used if the file is opened with `O_NONBLOCK`.
+    ///
+    /// The default implementation is the same as [`Self::read`].
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.read(writer)
+    }
+
     fn write(&self, reader: &mut VmReader) -> Result<usize>;
 
+    /// Returns whether the file is seekable.
+    ///
+    /// A seekable file is read and written at the file offset by [`Self::read_at`] and
+    /// [`Self::write_at`], instead of by [`Self::read`] and [`Self::write`].
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    /// Reads the file at `offset`.
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+
+    /// Writes the file at `offset`.
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         return_errno_with_message!(Errno::EINVAL, "ioctl is not supported");
     }
+
+    /// Returns the VMO to map when the file is mapped at `offset` by `mmap`,
+    /// along with the corresponding offset in the VMO.
+    ///
+    /// This is for the files whose inodes do not have page caches (e.g.,
+    /// device files that expose their buffers).
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        return_errno_with_message!(Errno::ENODEV, "mmap is not supported");
+    }
+}
+
+impl dyn FileIo {
+    pub fn downcast_ref<T: FileIo>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref::<T>()
+    }
 }
@@ -0,0 +1,403 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aster_rights::Rights;
+use ostd::sync::WaitQueue;
+
+use super::{
+    op::{IoUringOp, Request},
+    ring::{Cqe, Rings},
+    worker::WorkerPool,
+    IoUringFeatures, IoUringParams, IoUringRegisterOp, IoUringSetupFlags, IORING_MAX_CQ_ENTRIES,
+    IORING_MAX_ENTRIES, IORING_OFF_CQ_RING, IORING_OFF_SQES, IORING_OFF_SQ_RING,
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        file_table::{get_file_fast, FileDesc},
+        utils::{InodeMode, Metadata},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+    syscall::EventFile,
+    vm::vmo::{Vmo, VmoRightsOp},
+};
+
+/// The maximum number of fixed files.
+const IORING_MAX_FIXED_FILES: usize = 1 << 20;
+
+/// A file-like object that represents an io_uring instance.
+pub struct IoUringFile {
+    inner: Arc<IoUringInner>,
+    workers: Arc<WorkerPool>,
+    /// The files registered by `IORING_REGISTER_FILES`.
+    fixed_files: Mutex<Option<Box<[Option<Arc<dyn FileLike>>]>>>,
+}
+
+/// The part of an io_uring instance that is shared with the workers.
+pub(super) struct IoUringInner {
+    rings: Rings,
+    pollee: Pollee,
+    /// The wait queue that is woken up when a request completes.
+    cq_wait_queue: WaitQueue,
+    /// The number of completed requests, excluding the timeouts.
+    nr_completions: AtomicU64,
+    /// The eventfd registered by `IORING_REGISTER_EVENTFD`, which must be an [`EventFile`].
+    eventfd: Mutex<Option<Arc<dyn FileLike>>>,
+}
+
+impl IoUringFile {
+    /// Creates a new io_uring instance.
+    ///
+    /// The numbers of entries in `params` are adjusted, and the layout of the
+    /// rings is filled in `params`.
+    pub fn new(params: &mut IoUringParams) -> Result<Arc<Self>> {
+        let flags = IoUringSetupFlags::from_bits(params.flags)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid setup flags"))?;
+        if !IoUringSetupFlags::SUPPORTED.contains(flags) {
+            return_errno_with_message!(Errno::EINVAL, "the setup flags are not supported");
+        }
+        let is_clamped = flags.contains(IoUringSetupFlags::IORING_SETUP_CLAMP);
+
+        let mut sq_entries = params.sq_entries;
+        if sq_entries == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the number of SQ entries cannot be zero");
+        }
+        if sq_entries > IORING_MAX_ENTRIES {
+            if !is_clamped {
+                return_errno_with_message!(Errno::EINVAL, "too many SQ entries");
+            }
+            sq_entries = IORING_MAX_ENTRIES;
+        }
+        let sq_entries = sq_entries.next_power_of_two();
+
+        let cq_entries = if flags.contains(IoUringSetupFlags::IORING_SETUP_CQSIZE) {
+            let mut cq_entries = params.cq_entries;
+            if cq_entries == 0 {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the number of CQ entries cannot be zero"
+                );
+            }
+            if cq_entries > IORING_MAX_CQ_ENTRIES {
+                if !is_clamped {
+                    return_errno_with_message!(Errno::EINVAL, "too many CQ entries");
+                }
+                cq_entries = IORING_MAX_CQ_ENTRIES;
+            }
+            let cq_entries = cq_entries.next_power_of_two();
+            if cq_entries < sq_entries {
+                return_errno_with_message!(Errno::EINVAL, "the CQ cannot be smaller than the SQ");
+            }
+            cq_entries
+        } else {
+            2 * sq_entries
+        };
+
+        let rings = Rings::new(sq_entries, cq_entries)?;
+
+        params.sq_entries = sq_entries;
+        params.cq_entries = cq_entries;
+        params.features = (IoUringFeatures::IORING_FEAT_SINGLE_MMAP
+            | IoUringFeatures::IORING_FEAT_SUBMIT_STABLE
+            | IoUringFeatures::IORING_FEAT_RW_CUR_POS)
+            .bits();
+        params.sq_off = rings.sq_offsets();
+        params.cq_off = rings.cq_offsets();
+
+        let inner = Arc::new(IoUringInner {
+            rings,
+            pollee: Pollee::new(),
+            cq_wait_queue: WaitQueue::new(),
+            nr_completions: AtomicU64::new(0),
+            eventfd: Mutex::new(None),
+        });
+        Ok(Arc::new(Self {
+            inner,
+            workers: WorkerPool::new(),
+            fixed_files: Mutex::new(None),
+        }))
+    }
+
+    /// Submits at most `max_count` SQEs in the SQ.
+    ///
+    /// This method returns the number of the submitted SQEs. The SQEs that
+    /// fail to be prepared are completed with errors, and they are counted as
+    /// submitted.
+    ///
+    /// The requests that are in flight and the CQEs that are not consumed are
+    /// bounded by the size of the CQ, so that the completions never overflow
+    /// the CQ. If no SQEs can be submitted due to the bound, this method fails
+    /// with [`Errno::EBUSY`].
+    pub fn submit(&self, max_count: u32, ctx: &Context) -> Result<u32> {
+        let rings = &self.inner.rings;
+        let limit = |nr_sqes: u32| {
+            // Completing a request moves it from the in-flight ones to the unconsumed CQEs, so
+            // the sum never increases unless more SQEs are submitted.
+            let nr_occupied =
+                (self.workers.nr_in_flight() as u32).saturating_add(rings.nr_ready_cqes());
+            let nr_free = rings.cq_entries().saturating_sub(nr_occupied);
+            if nr_free == 0 && max_count > 0 && nr_sqes > 0 {
+                return_errno_with_message!(Errno::EBUSY, "too many requests are in flight");
+            }
+            Ok(max_count.min(nr_free))
+        };
+
+        rings.pop_sqes(limit, |sqe| {
+            let request = match Request::prepare(&sqe, self, ctx) {
+                Ok(request) => request,
+                Err(err) => {
+                    self.inner
+                        .complete(sqe.user_data, -(err.error() as i32), false);
+                    return;
+                }
+            };
+
+            if request.is_trivial() {
+                request.execute(&self.inner);
+                return;
+            }
+            self.workers.queue(request, self.inner.clone());
+        })
+    }
+
+    /// Waits until there are at least `min_count` CQEs in the CQ.
+    ///
+    /// The number is capped by the size of the CQ.
+    pub fn wait_cqes(&self, min_count: u32) -> Result<()> {
+        let min_count = min_count.min(self.inner.rings.cq_entries());
+        self.inner
+            .cq_wait_queue
+            .pause_until(|| (self.inner.rings.nr_ready_cqes() >= min_count).then_some(()))
+    }
+
+    /// Handles the operations of `io_uring_register`.
+    pub fn register(
+        &self,
+        op: IoUringRegisterOp,
+        arg: Vaddr,
+        nr_args: u32,
+        ctx: &Context,
+    ) -> Result<i32> {
+        match op {
+            IoUringRegisterOp::IORING_REGISTER_FILES => {
+                self.register_files(arg, nr_args, ctx)?;
+            }
+            IoUringRegisterOp::IORING_UNREGISTER_FILES => {
+                if self.fixed_files.lock().take().is_none() {
+                    return_errno_with_message!(Errno::ENXIO, "no files are registered");
+                }
+            }
+            IoUringRegisterOp::IORING_REGISTER_EVENTFD => {
+                if nr_args != 1 {
+                    return_errno_with_message!(Errno::EINVAL, "only one eventfd can be registered");
+                }
+                let fd = ctx.user_space().read_val::<FileDesc>(arg)?;
+                let file = {
+                    let mut file_table = ctx.thread_local.borrow_file_table_mut();
+                    get_file_fast!(&mut file_table, fd).into_owned()
+                };
+                if file.downcast_ref::<EventFile>().is_none() {
+                    return_errno_with_message!(Errno::EBADF, "the file is not an eventfd");
+                }
+
+                let mut eventfd = self.inner.eventfd.lock();
+                if eventfd.is_some() {
+                    return_errno_with_message!(Errno::EBUSY, "an eventfd is already registered");
+                }
+                *eventfd = Some(file);
+            }
+            IoUringRegisterOp::IORING_UNREGISTER_EVENTFD => {
+                if self.inner.eventfd.lock().take().is_none() {
+                    return_errno_with_message!(Errno::ENXIO, "no eventfd is registered");
+                }
+            }
+            IoUringRegisterOp::IORING_REGISTER_PROBE => {
+                self.write_probe(arg, nr_args, ctx)?;
+            }
+            _ => {
+                return_errno_with_message!(Errno::EINVAL, "the register operation is not supported")
+            }
+        }
+
+        Ok(0)
+    }
+
+    fn register_files(&self, arg: Vaddr, nr_args: u32, ctx: &Context) -> Result<()> {
+        let nr_files = nr_args as usize;
+        if nr_files == 0 || nr_files > IORING_MAX_FIXED_FILES {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of files");
+        }
+
+        let mut fixed_files = self.fixed_files.lock();
+        if fixed_files.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the files are already registered");
+        }
+
+        let user_space = ctx.user_space();
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        let mut files = Vec::with_capacity(nr_files);
+        for i in 0..nr_files {
+            let fd = user_space.read_val::<FileDesc>(arg + i * size_of::<FileDesc>())?;
+            // A negative file descriptor leaves a sparse slot.
+            if fd < 0 {
+                files.push(None);
+                continue;
+            }
+
+            let file = get_file_fast!(&mut file_table, fd).into_owned();
+            if file.downcast_ref::<IoUringFile>().is_some() {
+                return_errno_with_message!(Errno::EBADF, "io_uring files cannot be registered");
+            }
+            files.push(Some(file));
+        }
+
+        *fixed_files = Some(files.into_boxed_slice());
+        Ok(())
+    }
+
+    fn write_probe(&self, arg: Vaddr, nr_args: u32, ctx: &Context) -> Result<()> {
+        #[repr(C)]
+        #[derive(Debug, Default, Clone, Copy, Pod)]
+        struct IoUringProbeHeader {
+            last_op: u8,
+            ops_len: u8,
+            resv: u16,
+            resv2: [u32; 3],
+        }
+
+        #[repr(C)]
+        #[derive(Debug, Default, Clone, Copy, Pod)]
+        struct IoUringProbeOp {
+            op: u8,
+            resv: u8,
+            flags: u16,
+            resv2: u32,
+        }
+
+        const IO_URING_OP_SUPPORTED: u16 = 1 << 0;
+
+        let ops_len = (nr_args as usize).min(IoUringOp::LAST as usize + 1);
+        let header = IoUringProbeHeader {
+            last_op: IoUringOp::LAST,
+            ops_len: ops_len as u8,
+            ..Default::default()
+        };
+
+        let user_space = ctx.user_space();
+        user_space.write_val(arg, &header)?;
+        for op in 0..ops_len {
+            let probe_op = IoUringProbeOp {
+                op: op as u8,
+                flags: if IoUringOp::try_from(op as u8).is_ok() {
+                    IO_URING_OP_SUPPORTED
+                } else {
+                    0
+                },
+                ..Default::default()
+            };
+            let addr = arg + size_of::<IoUringProbeHeader>() + op * size_of::<IoUringProbeOp>();
+            user_space.write_val(addr, &probe_op)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the fixed file at `index`.
+    pub(super) fn fixed_file(&self, index: u32) -> Result<Arc<dyn FileLike>> {
+        self.fixed_files
+            .lock()
+            .as_ref()
+            .and_then(|files| files.get(index as usize)?.clone())
+            .ok_or_else(|| Error::with_message(Errno::EBADF, "the fixed file does not exist"))
+    }
+
+    pub(super) fn workers(&self) -> &Arc<WorkerPool> {
+        &self.workers
+    }
+
+    pub(super) fn inner(&self) -> &IoUringInner {
+        &self.inner
+    }
+}
+
+impl IoUringInner {
+    /// Posts the completion of a request.
+    pub(super) fn complete(&self, user_data: u64, res: i32, is_timeout: bool) {
+        self.rings.push_cqe(&Cqe {
+            user_data,
+            res,
+            flags: 0,
+        });
+        self.count_completion(is_timeout);
+
+        let eventfd = self.eventfd.lock().clone();
+        if let Some(eventfd) = eventfd {
+            eventfd.downcast_ref::<EventFile>().unwrap().signal();
+        }
+    }
+
+    /// Counts the completion of a request, which may not post a CQE.
+    pub(super) fn count_completion(&self, is_timeout: bool) {
+        if !is_timeout {
+            self.nr_completions.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.pollee.notify(IoEvents::IN);
+        self.cq_wait_queue.wake_all();
+    }
+
+    /// Returns the number of completed requests, excluding the timeouts.
+    pub(super) fn nr_completions(&self) -> u64 {
+        self.nr_completions.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn cq_wait_queue(&self) -> &WaitQueue {
+        &self.cq_wait_queue
+    }
+}
+
+impl Drop for IoUringFile {
+    fn drop(&mut self) {
+        self.workers.close();
+    }
+}
+
+impl Pollable for IoUringFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        // The CQEs can be consumed by the user space without notifying the kernel, so the
+        // events cannot be cached in the pollee.
+        if let Some(poller) = poller {
+            self.inner.pollee.register_poller(poller, mask);
+        }
+
+        let mut events = IoEvents::OUT;
+        if self.inner.rings.nr_ready_cqes() > 0 {
+            events |= IoEvents::IN;
+        }
+        events & mask
+    }
+}
+
+impl FileLike for IoUringFile {
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        let vmo = match offset {
+            IORING_OFF_SQ_RING | IORING_OFF_CQ_RING => self.inner.rings.rings_vmo(),
+            IORING_OFF_SQES => self.inner.rings.sqes_vmo(),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid io_uring mmap offset"),
+        };
+        Ok((vmo.dup()?, 0))
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `IoUringFile` to it.
+        Metadata::new_file(
+            0,
+            InodeMode::from_bits_truncate(0o600),
+            aster_block::BLOCK_SIZE,
+        )
+    }
+}
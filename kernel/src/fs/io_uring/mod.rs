@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The io_uring asynchronous I/O interface.
+//!
+//! An io_uring instance consists of a submission queue (SQ) and a completion
+//! queue (CQ), both of which are ring buffers shared with the user space by
+//! `mmap`. The user space puts submission queue entries (SQEs) into the SQ and
+//! calls `io_uring_enter` to submit them. The kernel executes the requests
+//! asynchronously on worker threads and puts completion queue entries (CQEs)
+//! into the CQ.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man7/io_uring.7.html>.
+
+use crate::prelude::*;
+
+mod file;
+mod op;
+mod ring;
+mod worker;
+
+pub use file::IoUringFile;
+
+/// The offset to `mmap` the SQ ring.
+pub const IORING_OFF_SQ_RING: usize = 0;
+/// The offset to `mmap` the CQ ring.
+pub const IORING_OFF_CQ_RING: usize = 0x8000000;
+/// The offset to `mmap` the SQE array.
+pub const IORING_OFF_SQES: usize = 0x10000000;
+
+/// The maximum number of SQ entries.
+const IORING_MAX_ENTRIES: u32 = 32768;
+/// The maximum number of CQ entries.
+const IORING_MAX_CQ_ENTRIES: u32 = 2 * IORING_MAX_ENTRIES;
+
+bitflags! {
+    /// The flags of `io_uring_setup`.
+    pub struct IoUringSetupFlags: u32 {
+        const IORING_SETUP_IOPOLL             = 1 << 0;
+        const IORING_SETUP_SQPOLL             = 1 << 1;
+        const IORING_SETUP_SQ_AFF             = 1 << 2;
+        const IORING_SETUP_CQSIZE             = 1 << 3;
+        const IORING_SETUP_CLAMP              = 1 << 4;
+        const IORING_SETUP_ATTACH_WQ          = 1 << 5;
+        const IORING_SETUP_R_DISABLED         = 1 << 6;
+        const IORING_SETUP_SUBMIT_ALL         = 1 << 7;
+        const IORING_SETUP_COOP_TASKRUN       = 1 << 8;
+        const IORING_SETUP_TASKRUN_FLAG       = 1 << 9;
+        const IORING_SETUP_SQE128             = 1 << 10;
+        const IORING_SETUP_CQE32              = 1 << 11;
+        const IORING_SETUP_SINGLE_ISSUER      = 1 << 12;
+        const IORING_SETUP_DEFER_TASKRUN      = 1 << 13;
+        const IORING_SETUP_NO_MMAP            = 1 << 14;
+        const IORING_SETUP_REGISTERED_FD_ONLY = 1 << 15;
+        const IORING_SETUP_NO_SQARRAY         = 1 << 16;
+    }
+}
+
+impl IoUringSetupFlags {
+    /// The flags that are supported.
+    ///
+    /// The task-running flags are mere hints, since the completions are
+    /// always posted by the workers without the help of the submitting task.
+    const SUPPORTED: Self = Self::IORING_SETUP_CQSIZE
+        .union(Self::IORING_SETUP_CLAMP)
+        .union(Self::IORING_SETUP_SUBMIT_ALL)
+        .union(Self::IORING_SETUP_COOP_TASKRUN)
+        .union(Self::IORING_SETUP_TASKRUN_FLAG)
+        .union(Self::IORING_SETUP_SINGLE_ISSUER);
+}
+
+bitflags! {
+    /// The features supported by the io_uring instances.
+    pub struct IoUringFeatures: u32 {
+        const IORING_FEAT_SINGLE_MMAP   = 1 << 0;
+        const IORING_FEAT_NODROP        = 1 << 1;
+        const IORING_FEAT_SUBMIT_STABLE = 1 << 2;
+        const IORING_FEAT_RW_CUR_POS    = 1 << 3;
+    }
+}
+
+bitflags! {
+    /// The flags of `io_uring_enter`.
+    pub struct IoUringEnterFlags: u32 {
+        const IORING_ENTER_GETEVENTS       = 1 << 0;
+        const IORING_ENTER_SQ_WAKEUP       = 1 << 1;
+        const IORING_ENTER_SQ_WAIT         = 1 << 2;
+        const IORING_ENTER_EXT_ARG         = 1 << 3;
+        const IORING_ENTER_REGISTERED_RING = 1 << 4;
+    }
+}
+
+/// The opcodes of `io_uring_register`.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub enum IoUringRegisterOp {
+    IORING_REGISTER_BUFFERS = 0,
+    IORING_UNREGISTER_BUFFERS = 1,
+    IORING_REGISTER_FILES = 2,
+    IORING_UNREGISTER_FILES = 3,
+    IORING_REGISTER_EVENTFD = 4,
+    IORING_UNREGISTER_EVENTFD = 5,
+    IORING_REGISTER_FILES_UPDATE = 6,
+    IORING_REGISTER_EVENTFD_ASYNC = 7,
+    IORING_REGISTER_PROBE = 8,
+}
+
+/// The parameters of `io_uring_setup`.
+///
+/// The user space provides the flags and (optionally) the numbers of
+/// entries. The kernel fills in the rest, which describe the layout of the
+/// rings.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub struct IoUringParams {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: IoSqringOffsets,
+    pub cq_off: IoCqringOffsets,
+}
+
+/// The offsets of the SQ ring fields in the `mmap`ed SQ ring.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub struct IoSqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
+
+/// The offsets of the CQ ring fields in the `mmap`ed CQ ring.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub struct IoCqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
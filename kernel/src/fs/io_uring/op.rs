@@ -0,0 +1,621 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_rights::Full;
+use ostd::sync::RwArc;
+
+use super::{
+    file::IoUringInner,
+    ring::Sqe,
+    worker::{Cancellation, WorkerPool},
+    IoUringFile,
+};
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::{get_file_fast, FdFlags, FileDesc, FileTable},
+        utils::{InodeType, StatusFlags},
+    },
+    net::socket::{MessageHeader, SendRecvFlags, SocketAddr},
+    prelude::*,
+    process::signal::Pause,
+    time::{
+        clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
+        timer::Timeout,
+        timespec_t,
+        wait::ManagedTimeout,
+    },
+    util::{
+        net::{read_socket_addr_from_user, socket_addr_to_c_bytes, SockFlags},
+        read_io_vecs_from_user,
+    },
+    vm::vmar::Vmar,
+};
+
+/// The opcodes of the io_uring requests that are supported.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub(super) enum IoUringOp {
+    IORING_OP_NOP = 0,
+    IORING_OP_READV = 1,
+    IORING_OP_WRITEV = 2,
+    IORING_OP_TIMEOUT = 11,
+    IORING_OP_ACCEPT = 13,
+    IORING_OP_ASYNC_CANCEL = 14,
+    IORING_OP_CONNECT = 16,
+    IORING_OP_READ = 22,
+    IORING_OP_WRITE = 23,
+    IORING_OP_SEND = 26,
+    IORING_OP_RECV = 27,
+}
+
+impl IoUringOp {
+    /// The last opcode that is supported.
+    pub(super) const LAST: u8 = Self::IORING_OP_RECV as u8;
+}
+
+bitflags! {
+    /// The flags of an SQE.
+    struct SqeFlags: u8 {
+        const IOSQE_FIXED_FILE       = 1 << 0;
+        const IOSQE_IO_DRAIN         = 1 << 1;
+        const IOSQE_IO_LINK          = 1 << 2;
+        const IOSQE_IO_HARDLINK      = 1 << 3;
+        const IOSQE_ASYNC            = 1 << 4;
+        const IOSQE_BUFFER_SELECT    = 1 << 5;
+        const IOSQE_CQE_SKIP_SUCCESS = 1 << 6;
+    }
+}
+
+bitflags! {
+    /// The flags of a timeout request.
+    struct TimeoutFlags: u32 {
+        const IORING_TIMEOUT_ABS      = 1 << 0;
+        const IORING_TIMEOUT_UPDATE   = 1 << 1;
+        const IORING_TIMEOUT_BOOTTIME = 1 << 2;
+        const IORING_TIMEOUT_REALTIME = 1 << 3;
+    }
+}
+
+/// The maximum number of bytes that can be transferred by a request.
+///
+/// This is the same as Linux's `MAX_RW_COUNT`, so that the result always fits in the CQE.
+const MAX_RW_COUNT: usize = i32::MAX as usize & !(PAGE_SIZE - 1);
+
+/// The maximum number of IO vectors.
+const UIO_MAXIOV: usize = 1024;
+
+/// The size of the kernel buffer through which a request transfers its data.
+///
+/// The data are transferred in chunks of this size, so the size of the
+/// kernel buffer does not depend on the length of the user buffers.
+const BUFFER_SIZE: usize = 16 * PAGE_SIZE;
+
+/// An io_uring request, which is prepared from an SQE.
+///
+/// The arguments that the request reads from the user space (e.g., the IO
+/// vectors) are copied when the request is prepared. The user buffers are
+/// accessed through the VMAR of the submitter, so that the request can be
+/// executed by a worker, which does not run in the address space of the
+/// submitter.
+pub(super) struct Request {
+    user_data: u64,
+    flags: SqeFlags,
+    op: Op,
+}
+
+enum Op {
+    Nop,
+    Read {
+        file: Arc<dyn FileLike>,
+        offset: Option<usize>,
+        bufs: Box<[(Vaddr, usize)]>,
+        vmar: Vmar<Full>,
+    },
+    Write {
+        file: Arc<dyn FileLike>,
+        offset: Option<usize>,
+        bufs: Box<[(Vaddr, usize)]>,
+        vmar: Vmar<Full>,
+    },
+    Accept {
+        file: Arc<dyn FileLike>,
+        /// The buffer of the peer address, i.e., its address, the address of its length, and
+        /// its length.
+        addr_buf: Option<(Vaddr, Vaddr, usize)>,
+        flags: SockFlags,
+        file_table: RwArc<FileTable>,
+        vmar: Vmar<Full>,
+    },
+    Connect {
+        file: Arc<dyn FileLike>,
+        addr: SocketAddr,
+    },
+    Send {
+        file: Arc<dyn FileLike>,
+        buf: (Vaddr, usize),
+        flags: SendRecvFlags,
+        vmar: Vmar<Full>,
+    },
+    Recv {
+        file: Arc<dyn FileLike>,
+        buf: (Vaddr, usize),
+        flags: SendRecvFlags,
+        vmar: Vmar<Full>,
+    },
+    Timeout {
+        timeout: Timeout,
+        flags: TimeoutFlags,
+        /// The number of completions at which the timeout is satisfied.
+        target: Option<u64>,
+    },
+    AsyncCancel {
+        /// The user data of the request to cancel.
+        target: u64,
+        workers: Arc<WorkerPool>,
+    },
+}
+
+impl Request {
+    /// Prepares a request from an SQE in the context of the submitter.
+    pub(super) fn prepare(sqe: &Sqe, io_uring: &IoUringFile, ctx: &Context) -> Result<Self> {
+        let flags = SqeFlags::from_bits(sqe.flags)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid SQE flags"))?;
+        if flags.intersects(
+            SqeFlags::IOSQE_IO_DRAIN
+                | SqeFlags::IOSQE_IO_LINK
+                | SqeFlags::IOSQE_IO_HARDLINK
+                | SqeFlags::IOSQE_BUFFER_SELECT,
+        ) {
+            return_errno_with_message!(Errno::EINVAL, "the SQE flags are not supported");
+        }
+
+        let opcode = IoUringOp::try_from(sqe.opcode)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the opcode is not supported"))?;
+        let get_file = || -> Result<Arc<dyn FileLike>> {
+            if flags.contains(SqeFlags::IOSQE_FIXED_FILE) {
+                return io_uring.fixed_file(sqe.fd as u32);
+            }
+            let mut file_table = ctx.thread_local.borrow_file_table_mut();
+            Ok(get_file_fast!(&mut file_table, sqe.fd as FileDesc).into_owned())
+        };
+        let offset = || (sqe.off != u64::MAX).then_some(sqe.off as usize);
+        let user_space = ctx.user_space();
+
+        let op = match opcode {
+            IoUringOp::IORING_OP_NOP => Op::Nop,
+            IoUringOp::IORING_OP_READ | IoUringOp::IORING_OP_READV => {
+                let bufs = if opcode == IoUringOp::IORING_OP_READ {
+                    Box::new([(sqe.addr as Vaddr, sqe.len as usize)]) as Box<[_]>
+                } else {
+                    check_iov_count(sqe.len)?;
+                    read_io_vecs_from_user(&user_space, sqe.addr as Vaddr, sqe.len as usize)?
+                };
+                Op::Read {
+                    file: get_file()?,
+                    offset: offset(),
+                    bufs,
+                    vmar: user_space.root_vmar().dup()?,
+                }
+            }
+            IoUringOp::IORING_OP_WRITE | IoUringOp::IORING_OP_WRITEV => {
+                let bufs = if opcode == IoUringOp::IORING_OP_WRITE {
+                    Box::new([(sqe.addr as Vaddr, sqe.len as usize)]) as Box<[_]>
+                } else {
+                    check_iov_count(sqe.len)?;
+                    read_io_vecs_from_user(&user_space, sqe.addr as Vaddr, sqe.len as usize)?
+                };
+                Op::Write {
+                    file: get_file()?,
+                    offset: offset(),
+                    bufs,
+                    vmar: user_space.root_vmar().dup()?,
+                }
+            }
+            IoUringOp::IORING_OP_ACCEPT => {
+                let addr_buf = if sqe.addr != 0 {
+                    let addr_len_ptr = sqe.off as Vaddr;
+                    let addr_len: i32 = user_space.read_val(addr_len_ptr)?;
+                    if addr_len < 0 {
+                        return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the socket address length cannot be negative"
+                        );
+                    }
+                    Some((sqe.addr as Vaddr, addr_len_ptr, addr_len as usize))
+                } else {
+                    None
+                };
+                let flags = SockFlags::from_bits(sqe.op_flags as i32)
+                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid accept flags"))?;
+                Op::Accept {
+                    file: get_file()?,
+                    addr_buf,
+                    flags,
+                    file_table: ctx.thread_local.borrow_file_table().unwrap().clone(),
+                    vmar: user_space.root_vmar().dup()?,
+                }
+            }
+            IoUringOp::IORING_OP_CONNECT => Op::Connect {
+                file: get_file()?,
+                addr: read_socket_addr_from_user(sqe.addr as Vaddr, sqe.off as usize)?,
+            },
+            IoUringOp::IORING_OP_SEND => Op::Send {
+                file: get_file()?,
+                buf: (sqe.addr as Vaddr, sqe.len as usize),
+                flags: SendRecvFlags::from_bits_truncate(sqe.op_flags as i32),
+                vmar: user_space.root_vmar().dup()?,
+            },
+            IoUringOp::IORING_OP_RECV => Op::Recv {
+                file: get_file()?,
+                buf: (sqe.addr as Vaddr, sqe.len as usize),
+                flags: SendRecvFlags::from_bits_truncate(sqe.op_flags as i32),
+                vmar: user_space.root_vmar().dup()?,
+            },
+            IoUringOp::IORING_OP_TIMEOUT => {
+                if sqe.len != 1 {
+                    return_errno_with_message!(Errno::EINVAL, "the timeout length must be one");
+                }
+                let flags = TimeoutFlags::from_bits(sqe.op_flags)
+                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid timeout flags"))?;
+                if flags.contains(TimeoutFlags::IORING_TIMEOUT_UPDATE)
+                    || flags.contains(
+                        TimeoutFlags::IORING_TIMEOUT_BOOTTIME
+                            | TimeoutFlags::IORING_TIMEOUT_REALTIME,
+                    )
+                {
+                    return_errno_with_message!(Errno::EINVAL, "invalid timeout flags");
+                }
+
+                let duration =
+                    Duration::try_from(user_space.read_val::<timespec_t>(sqe.addr as Vaddr)?)?;
+                let timeout = if flags.contains(TimeoutFlags::IORING_TIMEOUT_ABS) {
+                    Timeout::When(duration)
+                } else {
+                    Timeout::After(duration)
+                };
+                let target = (sqe.off != 0).then(|| io_uring.inner().nr_completions() + sqe.off);
+                Op::Timeout {
+                    timeout,
+                    flags,
+                    target,
+                }
+            }
+            IoUringOp::IORING_OP_ASYNC_CANCEL => {
+                if sqe.op_flags != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the cancel flags are not supported");
+                }
+                Op::AsyncCancel {
+                    target: sqe.addr,
+                    workers: io_uring.workers().clone(),
+                }
+            }
+        };
+
+        Ok(Self {
+            user_data: sqe.user_data,
+            flags,
+            op,
+        })
+    }
+
+    /// Returns the user data of the request.
+    pub(super) fn user_data(&self) -> u64 {
+        self.user_data
+    }
+
+    /// Returns whether the request can complete without being executed by a worker.
+    pub(super) fn is_trivial(&self) -> bool {
+        matches!(self.op, Op::Nop | Op::AsyncCancel { .. })
+    }
+
+    /// Posts the completion of the request as cancelled, without executing it.
+    pub(super) fn complete_cancelled(self, io_uring: &IoUringInner) {
+        let is_timeout = matches!(self.op, Op::Timeout { .. });
+        io_uring.complete(self.user_data, -(Errno::ECANCELED as i32), is_timeout);
+    }
+
+    /// Executes the request and posts the completion.
+    pub(super) fn execute(self, io_uring: &IoUringInner) {
+        let is_timeout = matches!(self.op, Op::Timeout { .. });
+        let res = match self.op.execute(io_uring) {
+            Ok(res) => res,
+            Err(err) => -(err.error() as i32),
+        };
+
+        if res >= 0 && self.flags.contains(SqeFlags::IOSQE_CQE_SKIP_SUCCESS) {
+            io_uring.count_completion(is_timeout);
+            return;
+        }
+        io_uring.complete(self.user_data, res, is_timeout);
+    }
+}
+
+impl Op {
+    fn execute(self, io_uring: &IoUringInner) -> Result<i32> {
+        match self {
+            Op::Nop => Ok(0),
+            Op::Read {
+                file,
+                offset,
+                bufs,
+                vmar,
+            } => {
+                // Reading a file that is not a regular file (e.g., a pipe) more than once may
+                // block even if some data have been read.
+                let is_chunked = file.metadata().type_ == InodeType::File;
+                let read_len = read_to_remote(&vmar, &bufs, is_chunked, |pos, buf| match offset {
+                    Some(offset) => file.read_bytes_at(offset + pos, buf),
+                    None => file.read_bytes(buf),
+                })?;
+                Ok(read_len as i32)
+            }
+            Op::Write {
+                file,
+                offset,
+                bufs,
+                vmar,
+            } => {
+                let written_len = write_from_remote(&vmar, &bufs, |pos, buf| match offset {
+                    Some(offset) => file.write_bytes_at(offset + pos, buf),
+                    None => file.write_bytes(buf),
+                })?;
+                Ok(written_len as i32)
+            }
+            Op::Accept {
+                file,
+                addr_buf,
+                flags,
+                file_table,
+                vmar,
+            } => {
+                let (connected_socket, socket_addr) = file.as_socket_or_err()?.accept()?;
+                if flags.contains(SockFlags::SOCK_NONBLOCK) {
+                    connected_socket.set_status_flags(StatusFlags::O_NONBLOCK)?;
+                }
+
+                if let Some((addr, addr_len_ptr, max_len)) = addr_buf {
+                    let bytes = socket_addr_to_c_bytes(&socket_addr);
+                    let written_len = bytes.len().min(max_len);
+                    copy_to_remote(&vmar, &[(addr, written_len)], 0, &bytes[..written_len])?;
+                    let actual_len = bytes.len() as i32;
+                    copy_to_remote(
+                        &vmar,
+                        &[(addr_len_ptr, size_of::<i32>())],
+                        0,
+                        actual_len.as_bytes(),
+                    )?;
+                }
+
+                let fd_flags = if flags.contains(SockFlags::SOCK_CLOEXEC) {
+                    FdFlags::CLOEXEC
+                } else {
+                    FdFlags::empty()
+                };
+                let fd = file_table.write().insert(connected_socket, fd_flags);
+                Ok(fd)
+            }
+            Op::Connect { file, addr } => {
+                file.as_socket_or_err()?.connect(addr)?;
+                Ok(0)
+            }
+            Op::Send {
+                file,
+                buf,
+                flags,
+                vmar,
+            } => {
+                let socket = file.as_socket_or_err()?;
+                // Like a blocking send on a stream socket, a large message is sent in several
+                // chunks. So a datagram larger than `BUFFER_SIZE` cannot be sent at once.
+                let sent_len = write_from_remote(&vmar, &[buf], |_, buf| {
+                    let mut reader = VmReader::from(buf).to_fallible();
+                    socket.sendmsg(&mut reader, MessageHeader::new(None, Vec::new()), flags)
+                })?;
+                Ok(sent_len as i32)
+            }
+            Op::Recv {
+                file,
+                buf,
+                flags,
+                vmar,
+            } => {
+                let socket = file.as_socket_or_err()?;
+                // A message is received at once, so a datagram larger than `BUFFER_SIZE` is
+                // truncated.
+                let received_len = read_to_remote(&vmar, &[buf], false, |_, buf| {
+                    let mut writer = VmWriter::from(buf).to_fallible();
+                    let (received_len, _) = socket.recvmsg(&mut writer, flags)?;
+                    Ok(received_len)
+                })?;
+                Ok(received_len as i32)
+            }
+            Op::Timeout {
+                timeout,
+                flags,
+                target,
+            } => {
+                let timer_manager = if flags.contains(TimeoutFlags::IORING_TIMEOUT_REALTIME) {
+                    RealTimeClock::timer_manager()
+                } else if flags.contains(TimeoutFlags::IORING_TIMEOUT_BOOTTIME) {
+                    BootTimeClock::timer_manager()
+                } else {
+                    MonotonicClock::timer_manager()
+                };
+                // The wait is interruptible, so that the timeout can be cancelled.
+                io_uring.cq_wait_queue().pause_until_or_timeout(
+                    || {
+                        target
+                            .filter(|target| io_uring.nr_completions() >= *target)
+                            .map(|_| 0)
+                    },
+                    ManagedTimeout::new_with_manager(timeout, timer_manager),
+                )
+            }
+            Op::AsyncCancel { target, workers } => match workers.cancel(target) {
+                Cancellation::Cancelled => Ok(0),
+                Cancellation::Interrupted => {
+                    return_errno_with_message!(Errno::EALREADY, "the request is being executed")
+                }
+                Cancellation::NotFound => {
+                    return_errno_with_message!(Errno::ENOENT, "the request is not found")
+                }
+            },
+        }
+    }
+}
+
+fn check_iov_count(count: u32) -> Result<()> {
+    if count as usize > UIO_MAXIOV {
+        return_errno_with_message!(Errno::EINVAL, "too many IO vectors");
+    }
+    Ok(())
+}
+
+/// Reads data to the user buffers via a kernel buffer, chunk by chunk.
+///
+/// The `read` closure reads the data at the given position (relative to the
+/// start of the request) into the kernel buffer. If `is_chunked` is false,
+/// only the first chunk is read. Reading also stops at the first short read.
+fn read_to_remote<F>(
+    vmar: &Vmar<Full>,
+    bufs: &[(Vaddr, usize)],
+    is_chunked: bool,
+    mut read: F,
+) -> Result<usize>
+where
+    F: FnMut(usize, &mut [u8]) -> Result<usize>,
+{
+    let len = total_len(bufs);
+    let mut buf = alloc_buffer(len)?;
+
+    let mut pos = 0;
+    loop {
+        let chunk_len = buf.len().min(len - pos);
+        let read_len = match read(pos, &mut buf[..chunk_len]) {
+            Ok(read_len) => read_len,
+            Err(_) if pos > 0 => break,
+            Err(err) => return Err(err),
+        };
+        // The returned length can be larger than the buffer (e.g., when receiving with
+        // `MSG_TRUNC`), but only the data in the buffer are copied.
+        copy_to_remote(vmar, bufs, pos, &buf[..read_len.min(chunk_len)])?;
+        pos += read_len;
+
+        if !is_chunked || read_len < chunk_len || pos == len {
+            break;
+        }
+    }
+
+    Ok(pos)
+}
+
+/// Writes data from the user buffers via a kernel buffer, chunk by chunk.
+///
+/// The `write` closure writes the data in the kernel buffer at the given
+/// position (relative to the start of the request). Writing stops at the
+/// first short write.
+fn write_from_remote<F>(vmar: &Vmar<Full>, bufs: &[(Vaddr, usize)], mut write: F) -> Result<usize>
+where
+    F: FnMut(usize, &[u8]) -> Result<usize>,
+{
+    let len = total_len(bufs);
+    let mut buf = alloc_buffer(len)?;
+
+    let mut pos = 0;
+    loop {
+        let chunk_len = buf.len().min(len - pos);
+        let res = copy_from_remote(vmar, bufs, pos, &mut buf[..chunk_len])
+            .and_then(|_| write(pos, &buf[..chunk_len]));
+        let written_len = match res {
+            Ok(written_len) => written_len,
+            Err(_) if pos > 0 => break,
+            Err(err) => return Err(err),
+        };
+        pos += written_len;
+
+        if written_len < chunk_len || pos == len {
+            break;
+        }
+    }
+
+    Ok(pos)
+}
+
+/// Returns the total length of the user buffers, truncated to [`MAX_RW_COUNT`].
+fn total_len(bufs: &[(Vaddr, usize)]) -> usize {
+    bufs.iter()
+        .fold(0usize, |total, (_, len)| total.saturating_add(*len))
+        .min(MAX_RW_COUNT)
+}
+
+/// Allocates a kernel buffer for a request whose length is `len`.
+///
+/// The buffer is no larger than [`BUFFER_SIZE`].
+fn alloc_buffer(len: usize) -> Result<Vec<u8>> {
+    let len = len.min(BUFFER_SIZE);
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(len)
+        .map_err(|_| Error::with_message(Errno::ENOMEM, "cannot allocate the buffer"))?;
+    buf.resize(len, 0);
+    Ok(buf)
+}
+
+/// Returns the parts of the user buffers that start at `pos` and cover `len` bytes.
+fn remote_chunks(
+    bufs: &[(Vaddr, usize)],
+    mut pos: usize,
+    len: usize,
+) -> impl Iterator<Item = (Vaddr, usize)> + '_ {
+    let mut remain = len;
+    bufs.iter().filter_map(move |&(addr, buf_len)| {
+        if pos >= buf_len {
+            pos -= buf_len;
+            return None;
+        }
+        let chunk = (addr + pos, (buf_len - pos).min(remain));
+        pos = 0;
+        remain -= chunk.1;
+        (chunk.1 > 0).then_some(chunk)
+    })
+}
+
+/// Copies the data from the user buffers, starting at `pos`, from a task that may not run in
+/// the address space.
+fn copy_from_remote(
+    vmar: &Vmar<Full>,
+    bufs: &[(Vaddr, usize)],
+    pos: usize,
+    data: &mut [u8],
+) -> Result<()> {
+    let mut copied_len = 0;
+    for (addr, len) in remote_chunks(bufs, pos, data.len()) {
+        let mut writer = VmWriter::from(&mut data[copied_len..copied_len + len]).to_fallible();
+        if vmar.read_remote(addr, &mut writer)? < len {
+            return_errno_with_message!(Errno::EFAULT, "the user buffer is not readable");
+        }
+        copied_len += len;
+    }
+    Ok(())
+}
+
+/// Copies the data to the user buffers, starting at `pos`, from a task that may not run in the
+/// address space.
+fn copy_to_remote(
+    vmar: &Vmar<Full>,
+    bufs: &[(Vaddr, usize)],
+    pos: usize,
+    data: &[u8],
+) -> Result<()> {
+    let mut copied_len = 0;
+    for (addr, len) in remote_chunks(bufs, pos, data.len()) {
+        let mut reader = VmReader::from(&data[copied_len..copied_len + len]).to_fallible();
+        if vmar.write_remote(addr, &mut reader)? < len {
+            return_errno_with_message!(Errno::EFAULT, "the user buffer is not writable");
+        }
+        copied_len += len;
+    }
+    Ok(())
+}
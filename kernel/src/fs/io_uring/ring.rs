@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{fence, Ordering};
+
+use aster_rights::Rights;
+use ostd::mm::{UFrame, UntypedMem};
+
+use super::{IoCqringOffsets, IoSqringOffsets};
+use crate::{
+    prelude::*,
+    vm::vmo::{CommitFlags, Vmo, VmoOptions},
+};
+
+/// A submission queue entry (SQE).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub(super) struct Sqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    /// The file offset, or the second address.
+    pub off: u64,
+    /// The buffer address.
+    pub addr: u64,
+    pub len: u32,
+    /// The opcode-specific flags.
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub file_index: i32,
+    pub addr3: u64,
+    pub __pad2: u64,
+}
+
+/// A completion queue entry (CQE).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub(super) struct Cqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
+
+// The layout of the rings, which is shared by the SQ ring and the CQ ring.
+//
+// The heads and the tails are put in different cache lines, since they are
+// written by different parties.
+const SQ_HEAD: usize = 0;
+const SQ_TAIL: usize = 64;
+const CQ_HEAD: usize = 128;
+const CQ_TAIL: usize = 192;
+const SQ_RING_MASK: usize = 256;
+const SQ_RING_ENTRIES: usize = 260;
+const CQ_RING_MASK: usize = 264;
+const CQ_RING_ENTRIES: usize = 268;
+const SQ_FLAGS: usize = 272;
+const SQ_DROPPED: usize = 276;
+const CQ_OVERFLOW: usize = 280;
+const CQ_FLAGS: usize = 284;
+const CQES: usize = 320;
+
+/// The rings of an io_uring instance.
+///
+/// The SQ ring and the CQ ring reside in the same memory, which is mapped by
+/// the user space at either [`IORING_OFF_SQ_RING`] or [`IORING_OFF_CQ_RING`].
+/// The SQEs reside in a separate memory, which is mapped at
+/// [`IORING_OFF_SQES`].
+///
+/// [`IORING_OFF_SQ_RING`]: super::IORING_OFF_SQ_RING
+/// [`IORING_OFF_CQ_RING`]: super::IORING_OFF_CQ_RING
+/// [`IORING_OFF_SQES`]: super::IORING_OFF_SQES
+pub(super) struct Rings {
+    rings: SharedMem,
+    sqes: SharedMem,
+    sq_entries: u32,
+    cq_entries: u32,
+    /// The offset of the SQ array, which maps the SQ ring to the SQE indexes.
+    sq_array: usize,
+    /// The SQ head, which is only written by the kernel.
+    sq_head: Mutex<u32>,
+    /// The CQ tail, which is only written by the kernel.
+    cq_tail: SpinLock<u32>,
+}
+
+impl Rings {
+    /// Allocates the rings.
+    ///
+    /// The numbers of entries must be powers of two.
+    pub(super) fn new(sq_entries: u32, cq_entries: u32) -> Result<Self> {
+        debug_assert!(sq_entries.is_power_of_two());
+        debug_assert!(cq_entries.is_power_of_two());
+
+        let sq_array = CQES + cq_entries as usize * size_of::<Cqe>();
+        let rings_size = sq_array + sq_entries as usize * size_of::<u32>();
+        let rings = SharedMem::new(rings_size)?;
+        let sqes = SharedMem::new(sq_entries as usize * size_of::<Sqe>())?;
+
+        rings.write_once(SQ_RING_MASK, sq_entries - 1);
+        rings.write_once(SQ_RING_ENTRIES, sq_entries);
+        rings.write_once(CQ_RING_MASK, cq_entries - 1);
+        rings.write_once(CQ_RING_ENTRIES, cq_entries);
+
+        Ok(Self {
+            rings,
+            sqes,
+            sq_entries,
+            cq_entries,
+            sq_array,
+            sq_head: Mutex::new(0),
+            cq_tail: SpinLock::new(0),
+        })
+    }
+
+    pub(super) fn cq_entries(&self) -> u32 {
+        self.cq_entries
+    }
+
+    /// Returns the offsets of the SQ ring fields.
+    pub(super) fn sq_offsets(&self) -> IoSqringOffsets {
+        IoSqringOffsets {
+            head: SQ_HEAD as u32,
+            tail: SQ_TAIL as u32,
+            ring_mask: SQ_RING_MASK as u32,
+            ring_entries: SQ_RING_ENTRIES as u32,
+            flags: SQ_FLAGS as u32,
+            dropped: SQ_DROPPED as u32,
+            array: self.sq_array as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the offsets of the CQ ring fields.
+    pub(super) fn cq_offsets(&self) -> IoCqringOffsets {
+        IoCqringOffsets {
+            head: CQ_HEAD as u32,
+            tail: CQ_TAIL as u32,
+            ring_mask: CQ_RING_MASK as u32,
+            ring_entries: CQ_RING_ENTRIES as u32,
+            overflow: CQ_OVERFLOW as u32,
+            cqes: CQES as u32,
+            flags: CQ_FLAGS as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the VMO of the SQ ring and the CQ ring.
+    pub(super) fn rings_vmo(&self) -> &Vmo<Rights> {
+        &self.rings.vmo
+    }
+
+    /// Returns the VMO of the SQEs.
+    pub(super) fn sqes_vmo(&self) -> &Vmo<Rights> {
+        &self.sqes.vmo
+    }
+
+    /// Pops SQEs from the SQ and handles them with `handle_sqe`.
+    ///
+    /// The number of the SQEs to pop is decided by `limit`, which is called with
+    /// the number of the SQEs in the SQ. Since concurrent pops are serialized,
+    /// `limit` sees a stable state of the SQ.
+    ///
+    /// The invalid SQE indexes are skipped and counted as dropped. This method
+    /// returns the number of the popped SQEs.
+    pub(super) fn pop_sqes(
+        &self,
+        limit: impl FnOnce(u32) -> Result<u32>,
+        mut handle_sqe: impl FnMut(Sqe),
+    ) -> Result<u32> {
+        let mut sq_head = self.sq_head.lock();
+
+        let sq_tail: u32 = self.rings.read_once(SQ_TAIL);
+        // Pairs with the release store of the SQ tail in the user space, so that the SQEs
+        // are visible after the tail is seen.
+        fence(Ordering::Acquire);
+
+        // The SQ tail is written by the user space, so it may be arbitrarily ahead of the SQ
+        // head. The SQ never holds more SQEs than its size, as in Linux.
+        let nr_sqes = sq_tail.wrapping_sub(*sq_head).min(self.sq_entries);
+        let count = limit(nr_sqes)?.min(nr_sqes);
+        for _ in 0..count {
+            let array_offset = self.sq_array + (*sq_head & (self.sq_entries - 1)) as usize * 4;
+            let index: u32 = self.rings.read_once(array_offset);
+            *sq_head = sq_head.wrapping_add(1);
+
+            if index >= self.sq_entries {
+                let dropped: u32 = self.rings.read_once(SQ_DROPPED);
+                self.rings.write_once(SQ_DROPPED, dropped.wrapping_add(1));
+                continue;
+            }
+            handle_sqe(self.sqes.read_val(index as usize * size_of::<Sqe>()));
+        }
+
+        // Pairs with the acquire load of the SQ head in the user space, so that the SQEs are
+        // no longer read when they are reused.
+        fence(Ordering::Release);
+        self.rings.write_once(SQ_HEAD, *sq_head);
+
+        Ok(count)
+    }
+
+    /// Pushes a CQE to the CQ.
+    ///
+    /// If the CQ is full, the CQE is dropped and counted as overflowed. This
+    /// method returns whether the CQE is pushed.
+    pub(super) fn push_cqe(&self, cqe: &Cqe) -> bool {
+        let mut cq_tail = self.cq_tail.lock();
+
+        let cq_head: u32 = self.rings.read_once(CQ_HEAD);
+        // Pairs with the release store of the CQ head in the user space, so that the consumed
+        // CQEs are not overwritten before being read.
+        fence(Ordering::Acquire);
+
+        if cq_tail.wrapping_sub(cq_head) >= self.cq_entries {
+            let overflow: u32 = self.rings.read_once(CQ_OVERFLOW);
+            self.rings.write_once(CQ_OVERFLOW, overflow.wrapping_add(1));
+            return false;
+        }
+
+        let cqe_offset = CQES + (*cq_tail & (self.cq_entries - 1)) as usize * size_of::<Cqe>();
+        self.rings.write_val(cqe_offset, cqe);
+        *cq_tail = cq_tail.wrapping_add(1);
+
+        // Pairs with the acquire load of the CQ tail in the user space, so that the CQE is
+        // visible after the tail is seen.
+        fence(Ordering::Release);
+        self.rings.write_once(CQ_TAIL, *cq_tail);
+
+        true
+    }
+
+    /// Returns the number of the CQEs that are not consumed by the user space.
+    pub(super) fn nr_ready_cqes(&self) -> u32 {
+        let cq_head: u32 = self.rings.read_once(CQ_HEAD);
+        let cq_tail = *self.cq_tail.lock();
+        cq_tail.wrapping_sub(cq_head)
+    }
+}
+
+/// The memory that is shared with the user space.
+///
+/// All the pages are committed in advance, so the memory can be accessed
+/// without handling page faults.
+struct SharedMem {
+    vmo: Vmo<Rights>,
+    frames: Vec<UFrame>,
+}
+
+impl SharedMem {
+    fn new(size: usize) -> Result<Self> {
+        let nr_pages = size.div_ceil(PAGE_SIZE);
+        let vmo = VmoOptions::<Rights>::new(nr_pages * PAGE_SIZE).alloc()?;
+        let frames = (0..nr_pages)
+            .map(|page_idx| vmo.commit_on(page_idx, CommitFlags::empty()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { vmo, frames })
+    }
+
+    // The values accessed below never cross page boundaries, since their sizes divide the page
+    // size and they are naturally aligned.
+
+    fn read_once(&self, offset: usize) -> u32 {
+        let mut reader = self.frames[offset / PAGE_SIZE].reader();
+        reader.skip(offset % PAGE_SIZE).read_once().unwrap()
+    }
+
+    fn write_once(&self, offset: usize, new_val: u32) {
+        let mut writer = self.frames[offset / PAGE_SIZE].writer();
+        writer
+            .skip(offset % PAGE_SIZE)
+            .write_once(&new_val)
+            .unwrap();
+    }
+
+    fn read_val<T: Pod>(&self, offset: usize) -> T {
+        debug_assert!(offset % PAGE_SIZE + size_of::<T>() <= PAGE_SIZE);
+        let mut reader = self.frames[offset / PAGE_SIZE].reader();
+        reader.skip(offset % PAGE_SIZE).read_val().unwrap()
+    }
+
+    fn write_val<T: Pod>(&self, offset: usize, new_val: &T) {
+        debug_assert!(offset % PAGE_SIZE + size_of::<T>() <= PAGE_SIZE);
+        let mut writer = self.frames[offset / PAGE_SIZE].writer();
+        writer.skip(offset % PAGE_SIZE).write_val(new_val).unwrap();
+    }
+}
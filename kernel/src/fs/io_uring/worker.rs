@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::collections::VecDeque;
+
+use ostd::sync::WaitQueue;
+
+use super::{file::IoUringInner, op::Request};
+use crate::{
+    prelude::*,
+    thread::{
+        kernel_thread::{AsKernelThread, ThreadOptions},
+        Thread,
+    },
+};
+
+/// The maximum number of workers of an io_uring instance.
+///
+/// The requests that may block for a long time (e.g., accepting connections)
+/// occupy the workers, so there should be enough workers to make progress on
+/// the other requests.
+const MAX_WORKERS: usize = 64;
+
+/// A request that is queued to be executed by a worker.
+struct Work {
+    request: Request,
+    io_uring: Arc<IoUringInner>,
+}
+
+/// A pool of kernel threads that execute the io_uring requests.
+///
+/// Workers are spawned on demand and exit when the pool is closed.
+pub(super) struct WorkerPool {
+    inner: SpinLock<WorkerPoolInner>,
+    wait_queue: WaitQueue,
+}
+
+struct WorkerPoolInner {
+    pending: VecDeque<Work>,
+    /// The workers that have started, indexed by the order in which they started.
+    workers: Vec<Worker>,
+    /// The number of workers, including those that have been spawned but not started.
+    nr_workers: usize,
+    nr_idle_workers: usize,
+    is_closed: bool,
+}
+
+struct Worker {
+    thread: Arc<Thread>,
+    /// The user data of the request that is being executed.
+    running: Option<u64>,
+}
+
+/// The result of cancelling a request by [`WorkerPool::cancel`].
+pub(super) enum Cancellation {
+    /// The request was pending. It has been removed and completed with `ECANCELED`.
+    Cancelled,
+    /// The request is being executed. Its worker has been interrupted.
+    Interrupted,
+    /// The request is not found.
+    NotFound,
+}
+
+impl WorkerPool {
+    pub(super) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(WorkerPoolInner {
+                pending: VecDeque::new(),
+                workers: Vec::new(),
+                nr_workers: 0,
+                nr_idle_workers: 0,
+                is_closed: false,
+            }),
+            wait_queue: WaitQueue::new(),
+        })
+    }
+
+    /// Queues the request to be executed by a worker.
+    pub(super) fn queue(self: &Arc<Self>, request: Request, io_uring: Arc<IoUringInner>) {
+        let should_spawn = {
+            let mut inner = self.inner.lock();
+            if inner.is_closed {
+                return;
+            }
+            inner.pending.push_back(Work { request, io_uring });
+
+            let should_spawn =
+                inner.nr_idle_workers < inner.pending.len() && inner.nr_workers < MAX_WORKERS;
+            if should_spawn {
+                inner.nr_workers += 1;
+            }
+            should_spawn
+        };
+
+        if should_spawn {
+            let pool = self.clone();
+            ThreadOptions::new(move || pool.run_worker()).spawn();
+        } else {
+            self.wait_queue.wake_one();
+        }
+    }
+
+    /// Returns the number of the requests that are pending or being executed.
+    pub(super) fn nr_in_flight(&self) -> usize {
+        let inner = self.inner.lock();
+        let nr_running = inner
+            .workers
+            .iter()
+            .filter(|worker| worker.running.is_some())
+            .count();
+        inner.pending.len() + nr_running
+    }
+
+    /// Cancels the request whose user data is `user_data`.
+    ///
+    /// This is the same as what `IORING_OP_ASYNC_CANCEL` does in Linux. A pending request is
+    /// removed and completed with `ECANCELED`. The worker that is executing the request is
+    /// interrupted, so the request will fail with `EINTR` if it is blocking.
+    pub(super) fn cancel(&self, user_data: u64) -> Cancellation {
+        let work = {
+            let mut inner = self.inner.lock();
+
+            if let Some(index) = inner
+                .pending
+                .iter()
+                .position(|work| work.request.user_data() == user_data)
+            {
+                inner.pending.remove(index).unwrap()
+            } else if let Some(worker) = inner
+                .workers
+                .iter()
+                .find(|worker| worker.running == Some(user_data))
+            {
+                worker.thread.as_kernel_thread().unwrap().interrupt();
+                return Cancellation::Interrupted;
+            } else {
+                return Cancellation::NotFound;
+            }
+        };
+
+        work.request.complete_cancelled(&work.io_uring);
+        Cancellation::Cancelled
+    }
+
+    /// Closes the pool.
+    ///
+    /// The pending requests are discarded, and the workers that are executing requests are
+    /// interrupted. This method returns after all the workers exit.
+    pub(super) fn close(&self) {
+        let (pending, threads) = {
+            let mut inner = self.inner.lock();
+            inner.is_closed = true;
+            for worker in inner.workers.iter() {
+                if worker.running.is_some() {
+                    worker.thread.as_kernel_thread().unwrap().interrupt();
+                }
+            }
+
+            let threads: Vec<_> = inner
+                .workers
+                .iter()
+                .map(|worker| worker.thread.clone())
+                .collect();
+            (core::mem::take(&mut inner.pending), threads)
+        };
+        // Drop the works without holding the lock.
+        drop(pending);
+
+        self.wait_queue.wake_all();
+
+        // The last reference to the io_uring instance may be dropped by one of its workers, which
+        // cannot join itself.
+        let current = Thread::current();
+        for thread in threads {
+            if current
+                .as_ref()
+                .is_some_and(|current| Arc::ptr_eq(current, &thread))
+            {
+                continue;
+            }
+            thread.join();
+        }
+    }
+
+    fn run_worker(&self) {
+        let thread = current_thread!();
+
+        let index = {
+            let mut inner = self.inner.lock();
+            if inner.is_closed {
+                inner.nr_workers -= 1;
+                return;
+            }
+            inner.workers.push(Worker {
+                thread: thread.clone(),
+                running: None,
+            });
+            inner.workers.len() - 1
+        };
+
+        while let Some(work) = self.next_work(index) {
+            work.request.execute(&work.io_uring);
+
+            // Clear the interruption with the lock held, so that an interruption that targets
+            // the finished request does not affect the next one.
+            let mut inner = self.inner.lock();
+            inner.workers[index].running = None;
+            thread.as_kernel_thread().unwrap().clear_interrupted();
+        }
+    }
+
+    /// Waits for the next work, or returns `None` if the pool is closed.
+    fn next_work(&self, index: usize) -> Option<Work> {
+        let mut is_idle = false;
+        self.wait_queue.wait_until(|| {
+            let mut inner = self.inner.lock();
+
+            let work = if inner.is_closed {
+                None
+            } else {
+                inner.pending.pop_front()
+            };
+            if work.is_none() && !inner.is_closed {
+                if !is_idle {
+                    inner.nr_idle_workers += 1;
+                    is_idle = true;
+                }
+                return None;
+            }
+
+            if is_idle {
+                inner.nr_idle_workers -= 1;
+            }
+            match work.as_ref() {
+                Some(work) => inner.workers[index].running = Some(work.request.user_data()),
+                None => inner.nr_workers -= 1,
+            }
+            Some(work)
+        })
+    }
+}
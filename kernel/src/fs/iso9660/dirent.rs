@@ -0,0 +1,390 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The directory records, including the Rock Ridge extensions in their system use areas.
+
+use core::time::Duration;
+
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+use crate::prelude::*;
+
+/// The size of the logical blocks, which is the only size that is supported.
+pub(super) const BLOCK_SIZE: usize = 2048;
+
+/// The size of a directory record without the file identifier.
+const RECORD_HEADER_SIZE: usize = 33;
+
+/// The maximum number of the continuation areas of a record, which prevents loops.
+const MAX_CONTINUATIONS: usize = 32;
+
+bitflags! {
+    /// The file flags of a directory record.
+    pub(super) struct FileFlags: u8 {
+        const HIDDEN       = 1 << 0;
+        const DIRECTORY    = 1 << 1;
+        const ASSOCIATED   = 1 << 2;
+        const RECORD       = 1 << 3;
+        const PROTECTION   = 1 << 4;
+        /// The file has more extents in the following records.
+        const MULTI_EXTENT = 1 << 7;
+    }
+}
+
+/// A directory record.
+#[derive(Debug, Clone)]
+pub(super) struct DirRecord {
+    /// The logical block of the extent.
+    pub(super) extent: u32,
+    /// The size of the extent.
+    pub(super) data_len: u32,
+    pub(super) recorded: Duration,
+    pub(super) flags: FileFlags,
+    /// The file identifier, which is `\0` for `.` and `\1` for `..`.
+    pub(super) identifier: Vec<u8>,
+    pub(super) system_use: Vec<u8>,
+}
+
+impl DirRecord {
+    /// Parses the record at the start of the bytes.
+    ///
+    /// Returns `None` if the length is zero, which means that there are no more records in the
+    /// logical block.
+    pub(super) fn parse(bytes: &[u8]) -> Result<Option<(Self, usize)>> {
+        let Some(&len) = bytes.first() else {
+            return Ok(None);
+        };
+        let len = len as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+        if len < RECORD_HEADER_SIZE + 1 || len > bytes.len() {
+            return_errno_with_message!(Errno::EIO, "the ISO 9660 directory record is invalid");
+        }
+
+        let ext_attr_len = bytes[1] as usize;
+        let name_len = bytes[32] as usize;
+        let name_end = RECORD_HEADER_SIZE + name_len;
+        if name_len == 0 || name_end > len {
+            return_errno_with_message!(Errno::EIO, "the ISO 9660 file identifier is invalid");
+        }
+        // The system use area starts at an even offset.
+        let system_use_start = (name_end + 1) & !1;
+
+        let record = Self {
+            extent: read_u32(bytes, 2).saturating_add(ext_attr_len as u32),
+            data_len: read_u32(bytes, 10),
+            recorded: parse_short_time(&bytes[18..25]),
+            flags: FileFlags::from_bits_truncate(bytes[25]),
+            identifier: bytes[RECORD_HEADER_SIZE..name_end].to_vec(),
+            system_use: bytes.get(system_use_start..len).unwrap_or(&[]).to_vec(),
+        };
+        Ok(Some((record, len)))
+    }
+
+    pub(super) fn is_dot(&self) -> bool {
+        self.identifier == [0]
+    }
+
+    pub(super) fn is_dotdot(&self) -> bool {
+        self.identifier == [1]
+    }
+
+    pub(super) fn is_dir(&self) -> bool {
+        self.flags.contains(FileFlags::DIRECTORY)
+    }
+}
+
+/// The name mapping of the records without Rock Ridge or Joliet names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NameMap {
+    /// The names are kept as they are.
+    Off,
+    /// The names are converted to lower case, and the versions and the trailing dots are removed.
+    Normal,
+}
+
+/// Decodes the name of a record in the primary volume descriptor.
+pub(super) fn decode_iso_name(identifier: &[u8], map: NameMap) -> String {
+    let mut name = String::from_utf8_lossy(identifier).into_owned();
+    if map == NameMap::Off {
+        return name;
+    }
+
+    if let Some(pos) = name.rfind(';') {
+        name.truncate(pos);
+    }
+    if name.ends_with('.') {
+        name.pop();
+    }
+    name.make_ascii_lowercase();
+    name
+}
+
+/// Decodes the name of a record in a Joliet volume descriptor, which is encoded in UCS-2.
+pub(super) fn decode_joliet_name(identifier: &[u8]) -> String {
+    let units = identifier
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+    let mut name: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if let Some(pos) = name.rfind(';') {
+        name.truncate(pos);
+    }
+    name
+}
+
+/// The attributes in the Rock Ridge entries of a record.
+#[derive(Debug, Default, Clone)]
+pub(super) struct RockRidge {
+    /// The `st_mode`, `st_nlink`, `st_uid`, and `st_gid` in `PX`.
+    pub(super) posix: Option<(u32, u32, u32, u32)>,
+    /// The alternate name in `NM`.
+    pub(super) name: Option<String>,
+    /// The target of the symlink in `SL`.
+    pub(super) symlink: Option<String>,
+    /// The device number in `PN`.
+    pub(super) rdev: Option<(u32, u32)>,
+    pub(super) mtime: Option<Duration>,
+    pub(super) atime: Option<Duration>,
+    pub(super) ctime: Option<Duration>,
+    /// The location of the relocated directory in `CL`.
+    pub(super) child_link: Option<u32>,
+    /// Whether the directory is relocated, which is marked by `RE` and is hidden.
+    pub(super) is_relocated: bool,
+    /// The number of the bytes to skip in the system use areas, which is in `SP`.
+    pub(super) sp_skip: Option<u8>,
+}
+
+impl RockRidge {
+    /// Parses the entries in the system use area, whose first `skip` bytes are skipped.
+    ///
+    /// The continuation areas in `CE` are read with `read_area`.
+    pub(super) fn parse(
+        system_use: &[u8],
+        skip: usize,
+        read_area: &dyn Fn(u32, u32, u32) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        let mut rock_ridge = Self::default();
+        let mut name = Vec::new();
+        let mut symlink = SymlinkBuilder::default();
+
+        let mut area = system_use.get(skip..).unwrap_or(&[]).to_vec();
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+            let mut entries = area.as_slice();
+
+            while entries.len() >= 4 {
+                let signature = [entries[0], entries[1]];
+                let len = entries[2] as usize;
+                if len < 4 || len > entries.len() {
+                    break;
+                }
+                let data = &entries[4..len];
+                entries = &entries[len..];
+
+                match &signature {
+                    b"SP" if data.len() >= 3 && data[..2] == [0xBE, 0xEF] => {
+                        rock_ridge.sp_skip = Some(data[2]);
+                    }
+                    b"PX" if data.len() >= 32 => {
+                        rock_ridge.posix = Some((
+                            read_u32(data, 0),
+                            read_u32(data, 8),
+                            read_u32(data, 16),
+                            read_u32(data, 24),
+                        ));
+                    }
+                    b"PN" if data.len() >= 16 => {
+                        rock_ridge.rdev = Some((read_u32(data, 0), read_u32(data, 8)));
+                    }
+                    b"NM" if !data.is_empty() => {
+                        // The names of `.` and `..` are never replaced.
+                        if data[0] & (NM_CURRENT | NM_PARENT) == 0 {
+                            name.extend_from_slice(&data[1..]);
+                        }
+                    }
+                    b"SL" if !data.is_empty() => symlink.push_entry(&data[1..]),
+                    b"TF" if !data.is_empty() => rock_ridge.parse_times(data[0], &data[1..]),
+                    b"CL" if data.len() >= 8 => rock_ridge.child_link = Some(read_u32(data, 0)),
+                    b"RE" => rock_ridge.is_relocated = true,
+                    b"CE" if data.len() >= 24 => {
+                        continuation =
+                            Some((read_u32(data, 0), read_u32(data, 8), read_u32(data, 16)));
+                    }
+                    b"ST" => break,
+                    _ => (),
+                }
+            }
+
+            let Some((block, offset, len)) = continuation else {
+                break;
+            };
+            area = read_area(block, offset, len)?;
+        }
+
+        if !name.is_empty() {
+            rock_ridge.name = Some(String::from_utf8_lossy(&name).into_owned());
+        }
+        rock_ridge.symlink = symlink.finish();
+        Ok(rock_ridge)
+    }
+
+    fn parse_times(&mut self, flags: u8, mut data: &[u8]) {
+        let is_long = flags & TF_LONG_FORM != 0;
+        let size = if is_long { 17 } else { 7 };
+
+        for bit in 0..7 {
+            if flags & (1 << bit) == 0 {
+                continue;
+            }
+            let Some(bytes) = data.get(..size) else {
+                return;
+            };
+            data = &data[size..];
+
+            let time = if is_long {
+                parse_long_time(bytes)
+            } else {
+                parse_short_time(bytes)
+            };
+            match 1 << bit {
+                TF_MODIFY => self.mtime = Some(time),
+                TF_ACCESS => self.atime = Some(time),
+                TF_ATTRIBUTES => self.ctime = Some(time),
+                _ => (),
+            }
+        }
+    }
+}
+
+const NM_CURRENT: u8 = 1 << 1;
+const NM_PARENT: u8 = 1 << 2;
+
+const TF_MODIFY: u8 = 1 << 1;
+const TF_ACCESS: u8 = 1 << 2;
+const TF_ATTRIBUTES: u8 = 1 << 3;
+const TF_LONG_FORM: u8 = 1 << 7;
+
+const SL_CONTINUE: u8 = 1 << 0;
+const SL_CURRENT: u8 = 1 << 1;
+const SL_PARENT: u8 = 1 << 2;
+const SL_ROOT: u8 = 1 << 3;
+
+/// The builder of the symlink target in the `SL` entries.
+#[derive(Default)]
+struct SymlinkBuilder {
+    target: Option<String>,
+    /// Whether the last component is continued by the next one.
+    is_continued: bool,
+}
+
+impl SymlinkBuilder {
+    fn push_entry(&mut self, mut components: &[u8]) {
+        while components.len() >= 2 {
+            let flags = components[0];
+            let len = components[1] as usize;
+            let Some(content) = components.get(2..2 + len) else {
+                break;
+            };
+            components = &components[2 + len..];
+
+            let target = self.target.get_or_insert_with(String::new);
+            if !self.is_continued && !target.is_empty() && !target.ends_with('/') {
+                target.push('/');
+            }
+            if flags & SL_ROOT != 0 {
+                target.clear();
+                target.push('/');
+            } else if flags & SL_CURRENT != 0 {
+                target.push('.');
+            } else if flags & SL_PARENT != 0 {
+                target.push_str("..");
+            } else {
+                target.push_str(&String::from_utf8_lossy(content));
+            }
+            self.is_continued = flags & SL_CONTINUE != 0;
+        }
+    }
+
+    fn finish(self) -> Option<String> {
+        self.target
+    }
+}
+
+/// Parses a 7-byte timestamp, which is in the directory records and the short form of `TF`.
+fn parse_short_time(bytes: &[u8]) -> Duration {
+    to_duration(
+        1900 + bytes[0] as i32,
+        bytes[1],
+        bytes[2],
+        bytes[3],
+        bytes[4],
+        bytes[5],
+        0,
+        bytes[6] as i8,
+    )
+}
+
+/// Parses a 17-byte timestamp, which is in the volume descriptors and the long form of `TF`.
+fn parse_long_time(bytes: &[u8]) -> Duration {
+    let digits = |range: core::ops::Range<usize>| {
+        bytes[range].iter().fold(0u32, |value, &digit| {
+            value * 10 + digit.wrapping_sub(b'0').min(9) as u32
+        })
+    };
+    to_duration(
+        digits(0..4) as i32,
+        digits(4..6) as u8,
+        digits(6..8) as u8,
+        digits(8..10) as u8,
+        digits(10..12) as u8,
+        digits(12..14) as u8,
+        digits(14..16) * 10_000_000,
+        bytes[16] as i8,
+    )
+}
+
+/// Converts a local time with the offset from UTC in 15-minute intervals to a duration since
+/// the UNIX epoch.
+///
+/// The invalid fields are treated as the minimum valid values, like other implementations.
+#[expect(clippy::too_many_arguments)]
+fn to_duration(
+    year: i32,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanos: u32,
+    gmt_offset: i8,
+) -> Duration {
+    if year == 1900 && month == 0 {
+        // The time is not specified.
+        return Duration::ZERO;
+    }
+
+    let month = Month::try_from(month).unwrap_or(Month::January);
+    let date = Date::from_calendar_date(year, month, day.max(1))
+        .or_else(|_| Date::from_calendar_date(year, month, 1))
+        .unwrap_or(Date::MIN);
+    let time = Time::from_hms(hour.min(23), minute.min(59), second.min(59)).unwrap();
+
+    let secs = PrimitiveDateTime::new(date, time)
+        .assume_utc()
+        .unix_timestamp()
+        .saturating_sub(gmt_offset as i64 * 15 * 60)
+        .max(0);
+    Duration::new(secs as u64, nanos)
+}
+
+/// Reads the little-endian half of a both-endian 32-bit field.
+pub(super) fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads the little-endian half of a both-endian 16-bit field.
+pub(super) fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::BlockDevice;
+use hashbrown::HashMap;
+use ostd::mm::VmIo;
+use spin::Once;
+
+use super::{
+    dirent::{
+        decode_iso_name, decode_joliet_name, read_u16, read_u32, DirRecord, FileFlags, NameMap,
+        RockRidge, BLOCK_SIZE,
+    },
+    inode::Iso9660Inode,
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, InodeMode, InodeType, SuperBlock},
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The magic number of ISO 9660, which is the same as Linux's `ISOFS_SUPER_MAGIC`.
+const ISO9660_MAGIC: u64 = 0x9660;
+/// The maximum length of the names, which is limited by the `NM` entries of Rock Ridge.
+const MAX_NAME_LEN: usize = 255;
+
+/// The logical block of the first volume descriptor, after the 32 KiB system area.
+///
+/// The system area is where hybrid images put their MBR or GPT, so it is never parsed here.
+const FIRST_DESCRIPTOR_BLOCK: usize = 16;
+/// The maximum number of the volume descriptors to look for the terminator.
+const MAX_DESCRIPTORS: usize = 64;
+
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+const DESCRIPTOR_TERMINATOR: u8 = 255;
+
+/// The escape sequences of the Joliet supplementary volume descriptors for UCS-2 levels 1-3.
+const JOLIET_ESCAPES: [&[u8; 3]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+/// A read-only ISO 9660 file system, which is used by CD/DVD images and installer media.
+pub struct Iso9660FS {
+    block_device: Arc<dyn BlockDevice>,
+    options: Iso9660MountOptions,
+    /// The number of the logical blocks in the volume.
+    num_blocks: u32,
+    /// The format of the directory tree that is used.
+    format: TreeFormat,
+    root: Once<Arc<Iso9660Inode>>,
+    /// The opened inodes, indexed by the inode numbers.
+    inodes: RwMutex<HashMap<u64, Arc<Iso9660Inode>>>,
+}
+
+/// The format of the directory tree, which determines how the records are interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum TreeFormat {
+    /// The primary directory tree with the Rock Ridge entries after the first `skip` bytes of
+    /// the system use areas.
+    RockRidge { skip: usize },
+    /// The directory tree of a Joliet supplementary volume descriptor.
+    Joliet,
+    /// The primary directory tree with the plain ISO 9660 names.
+    Plain,
+}
+
+impl Iso9660FS {
+    /// Opens an ISO 9660 volume on the `block_device`.
+    pub fn open(
+        block_device: Arc<dyn BlockDevice>,
+        options: Iso9660MountOptions,
+    ) -> Result<Arc<Self>> {
+        let (primary, joliet) = Self::read_descriptors(block_device.as_ref())?;
+        let num_blocks = read_u32(&primary, 80);
+        let primary_root = Self::parse_root_record(&primary)?;
+
+        let mut fs = Self {
+            block_device,
+            options,
+            num_blocks,
+            format: TreeFormat::Plain,
+            root: Once::new(),
+            inodes: RwMutex::new(HashMap::new()),
+        };
+
+        // Like Linux, Rock Ridge is preferred to Joliet, since it records the POSIX attributes.
+        let mut root_record = primary_root;
+        if let Some(skip) = fs.detect_rock_ridge(&root_record)? {
+            fs.format = TreeFormat::RockRidge { skip };
+        } else if let Some(joliet) = joliet.filter(|_| !fs.options.nojoliet) {
+            root_record = Self::parse_root_record(&joliet)?;
+            fs.format = TreeFormat::Joliet;
+        }
+
+        let fs = Arc::new(fs);
+        let root = Iso9660Inode::new_root(&fs, &root_record)?;
+        fs.root.call_once(|| root);
+        Ok(fs)
+    }
+
+    /// Reads the primary volume descriptor and the Joliet supplementary volume descriptor.
+    fn read_descriptors(block_device: &dyn BlockDevice) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let mut primary = None;
+        let mut joliet = None;
+
+        for index in 0..MAX_DESCRIPTORS {
+            let mut descriptor = vec![0u8; BLOCK_SIZE];
+            block_device.read_bytes(
+                (FIRST_DESCRIPTOR_BLOCK + index) * BLOCK_SIZE,
+                &mut descriptor,
+            )?;
+            if &descriptor[1..6] != b"CD001" {
+                return_errno_with_message!(Errno::EINVAL, "the volume descriptor is invalid");
+            }
+
+            match descriptor[0] {
+                DESCRIPTOR_PRIMARY if primary.is_none() => primary = Some(descriptor),
+                DESCRIPTOR_SUPPLEMENTARY
+                    if joliet.is_none()
+                        && JOLIET_ESCAPES
+                            .iter()
+                            .any(|escape| descriptor[88..91] == escape[..]) =>
+                {
+                    joliet = Some(descriptor)
+                }
+                DESCRIPTOR_TERMINATOR => break,
+                // The boot records of El Torito and the partition descriptors are only used by
+                // the firmware and the boot loaders.
+                _ => (),
+            }
+        }
+
+        let primary = primary.ok_or_else(|| {
+            Error::with_message(
+                Errno::EINVAL,
+                "the primary volume descriptor does not exist",
+            )
+        })?;
+        if read_u16(&primary, 128) as usize != BLOCK_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "the logical block size is not supported");
+        }
+        Ok((primary, joliet))
+    }
+
+    fn parse_root_record(descriptor: &[u8]) -> Result<DirRecord> {
+        match DirRecord::parse(&descriptor[156..190])? {
+            Some((record, _)) if record.is_dir() => Ok(record),
+            _ => return_errno_with_message!(Errno::EINVAL, "the root directory record is invalid"),
+        }
+    }
+
+    /// Returns the number of the bytes to skip in the system use areas if the primary
+    /// directory tree has Rock Ridge entries, which is marked by `SP` in the root `.` record.
+    fn detect_rock_ridge(&self, root_record: &DirRecord) -> Result<Option<usize>> {
+        if self.options.norock {
+            return Ok(None);
+        }
+
+        let block = self.read_block(root_record.extent)?;
+        let Some((dot, _)) = DirRecord::parse(&block)? else {
+            return Ok(None);
+        };
+        let rock_ridge = self.parse_rock_ridge(&dot, 0)?;
+        Ok(rock_ridge.sp_skip.map(|skip| skip as usize))
+    }
+
+    pub(super) fn parse_rock_ridge(&self, record: &DirRecord, skip: usize) -> Result<RockRidge> {
+        RockRidge::parse(&record.system_use, skip, &|block, offset, len| {
+            if offset as usize + len as usize > BLOCK_SIZE {
+                return_errno_with_message!(Errno::EIO, "the continuation area is invalid");
+            }
+            let block = self.read_block(block)?;
+            Ok(block[offset as usize..(offset + len) as usize].to_vec())
+        })
+    }
+
+    pub(super) fn read_block(&self, block: u32) -> Result<Vec<u8>> {
+        if block >= self.num_blocks {
+            return_errno_with_message!(Errno::EIO, "the block is beyond the volume");
+        }
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        self.block_device
+            .read_bytes(block as usize * BLOCK_SIZE, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Reads the entries of the directory whose extent starts at `extent`.
+    pub(super) fn read_dir(&self, extent: u32, size: usize) -> Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        // The extents of a multi-extent file, which are in the consecutive records.
+        let mut pending: Vec<(u32, u32)> = Vec::new();
+
+        for block_index in 0..size.div_ceil(BLOCK_SIZE) {
+            let block_no = extent + block_index as u32;
+            let block = self.read_block(block_no)?;
+            let mut pos = 0;
+
+            // The records never cross the logical blocks.
+            while pos < BLOCK_SIZE {
+                let Some((record, len)) = DirRecord::parse(&block[pos..])? else {
+                    break;
+                };
+                let record_pos = block_no as usize * BLOCK_SIZE + pos;
+                pos += len;
+                if record.is_dot() || record.is_dotdot() {
+                    continue;
+                }
+
+                pending.push((record.extent, record.data_len));
+                if record.flags.contains(FileFlags::MULTI_EXTENT) {
+                    continue;
+                }
+                let extents = core::mem::take(&mut pending);
+                if let Some(entry) = self.new_entry(record, record_pos, extents)? {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Creates the entry of the last `record` of a file, or returns `None` if it is hidden.
+    fn new_entry(
+        &self,
+        mut record: DirRecord,
+        record_pos: usize,
+        mut extents: Vec<(u32, u32)>,
+    ) -> Result<Option<DirEntry>> {
+        let rock_ridge = match self.format {
+            TreeFormat::RockRidge { skip } => Some(self.parse_rock_ridge(&record, skip)?),
+            _ => None,
+        };
+
+        let name = match &rock_ridge {
+            Some(RockRidge {
+                name: Some(name), ..
+            }) => name.clone(),
+            _ if self.format == TreeFormat::Joliet => decode_joliet_name(&record.identifier),
+            _ => decode_iso_name(&record.identifier, self.options.map),
+        };
+
+        if let Some(rock_ridge) = &rock_ridge {
+            // The relocated directories are shown at their original locations.
+            if rock_ridge.is_relocated {
+                return Ok(None);
+            }
+            if let Some(child) = rock_ridge.child_link {
+                let block = self.read_block(child)?;
+                let Some((dot, _)) = DirRecord::parse(&block)? else {
+                    return_errno_with_message!(Errno::EIO, "the relocated directory is invalid");
+                };
+                record.flags |= FileFlags::DIRECTORY;
+                extents = vec![(dot.extent, dot.data_len)];
+            }
+        }
+
+        let type_ = match rock_ridge.as_ref().and_then(|rock_ridge| rock_ridge.posix) {
+            Some((mode, ..)) => InodeType::from_raw_mode(mode as u16)?,
+            None if record.flags.contains(FileFlags::DIRECTORY) => InodeType::Dir,
+            None => InodeType::File,
+        };
+        // Like Linux, the directories are identified by their `.` records, so that the entries
+        // in their parents and the `.` entries have the same inode numbers.
+        let ino = match type_ {
+            InodeType::Dir => extents[0].0 as u64 * BLOCK_SIZE as u64,
+            _ => record_pos as u64,
+        };
+
+        Ok(Some(DirEntry {
+            name,
+            ino,
+            type_,
+            record,
+            extents,
+            rock_ridge,
+        }))
+    }
+
+    pub(super) fn block_device(&self) -> &Arc<dyn BlockDevice> {
+        &self.block_device
+    }
+
+    pub(super) fn options(&self) -> &Iso9660MountOptions {
+        &self.options
+    }
+
+    pub(super) fn format(&self) -> TreeFormat {
+        self.format
+    }
+
+    pub(super) fn root(&self) -> &Arc<Iso9660Inode> {
+        self.root.get().unwrap()
+    }
+
+    pub(super) fn find_inode(&self, ino: u64) -> Option<Arc<Iso9660Inode>> {
+        self.inodes.read().get(&ino).cloned()
+    }
+
+    pub(super) fn insert_inode(&self, ino: u64, inode: Arc<Iso9660Inode>) {
+        self.inodes.write().insert(ino, inode);
+    }
+}
+
+/// An entry of a directory.
+#[derive(Debug)]
+pub(super) struct DirEntry {
+    pub(super) name: String,
+    pub(super) ino: u64,
+    pub(super) type_: InodeType,
+    /// The last record of the file.
+    pub(super) record: DirRecord,
+    /// The logical blocks and the sizes of the extents.
+    pub(super) extents: Vec<(u32, u32)>,
+    pub(super) rock_ridge: Option<RockRidge>,
+}
+
+impl FileSystem for Iso9660FS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root().clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(ISO9660_MAGIC, BLOCK_SIZE, MAX_NAME_LEN);
+        sb.blocks = self.num_blocks as usize;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+impl Debug for Iso9660FS {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Iso9660FS")
+            .field("num_blocks", &self.num_blocks)
+            .field("format", &self.format)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The mount options of the ISO 9660 file system.
+#[derive(Clone, Debug)]
+pub struct Iso9660MountOptions {
+    /// Whether the Rock Ridge entries are ignored.
+    pub(super) norock: bool,
+    /// Whether the Joliet names are ignored.
+    pub(super) nojoliet: bool,
+    /// The owner of the files without Rock Ridge attributes.
+    pub(super) uid: Uid,
+    /// The group of the files without Rock Ridge attributes.
+    pub(super) gid: Gid,
+    /// The permission bits of the files without Rock Ridge attributes.
+    pub(super) mode: InodeMode,
+    /// The permission bits of the directories without Rock Ridge attributes.
+    pub(super) dmode: InodeMode,
+    /// The mapping of the plain ISO 9660 names.
+    pub(super) map: NameMap,
+}
+
+impl Default for Iso9660MountOptions {
+    fn default() -> Self {
+        Self {
+            norock: false,
+            nojoliet: false,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            mode: InodeMode::from_bits_truncate(0o444),
+            dmode: InodeMode::from_bits_truncate(0o555),
+            map: NameMap::Normal,
+        }
+    }
+}
+
+impl Iso9660MountOptions {
+    /// Parses the options from the mount data, e.g., `nojoliet,uid=1000,mode=0444`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let parse_num = |radix| {
+                u32::from_str_radix(value, radix).map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the iso9660 mount option is invalid")
+                })
+            };
+            let parse_mode =
+                || parse_num(8).map(|mode| InodeMode::from_bits_truncate((mode & 0o7777) as u16));
+
+            match key {
+                "norock" => options.norock = true,
+                "nojoliet" => options.nojoliet = true,
+                "uid" => options.uid = Uid::new(parse_num(10)?),
+                "gid" => options.gid = Gid::new(parse_num(10)?),
+                "mode" => options.mode = parse_mode()?,
+                "dmode" => options.dmode = parse_mode()?,
+                "map" => {
+                    options.map = match value {
+                        "normal" | "n" => NameMap::Normal,
+                        "off" | "o" => NameMap::Off,
+                        _ => return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the name mapping is not supported"
+                        ),
+                    }
+                }
+                // The names are always UTF-8 strings in the VFS.
+                "iocharset" if value == "utf8" || value == "utf-8" => (),
+                "utf8" => (),
+                "iocharset" => {
+                    return_errno_with_message!(Errno::EINVAL, "only UTF-8 names are supported")
+                }
+                // The volume is always read-only.
+                "ro" => (),
+                _ => {
+                    return_errno_with_message!(Errno::EINVAL, "the iso9660 mount option is unknown")
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,508 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use align_ext::AlignExt;
+use aster_block::{
+    bio::{Bio, BioDirection, BioSegment, BioType, BioWaiter},
+    id::Sid,
+};
+use aster_rights::Full;
+use ostd::mm::{Segment, VmIo};
+use spin::Once;
+
+use super::{
+    dirent::{DirRecord, NameMap, BLOCK_SIZE},
+    fs::{DirEntry, Iso9660FS, TreeFormat},
+};
+use crate::{
+    fs::{
+        device::{Device, DeviceId},
+        path::is_dot,
+        utils::{
+            CachePage, DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType,
+            PageCache, PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The number of the `.` and `..` entries, which are emitted first by `readdir_at`.
+const NUM_SPECIAL_ENTRIES: usize = 2;
+
+/// An inode of the ISO 9660 file system.
+///
+/// The inodes are never changed, so all the metadata is loaded when the inodes are created.
+pub struct Iso9660Inode {
+    ino: u64,
+    type_: InodeType,
+    meta: InodeMeta,
+    /// The logical blocks and the sizes of the extents.
+    extents: Vec<(u32, u32)>,
+    /// The parent directory, which is `None` for the root directory.
+    parent: Option<Arc<Iso9660Inode>>,
+    /// The entries of the directory, which are read when they are first used.
+    entries: Once<Vec<DirEntry>>,
+    /// The target of the symlink.
+    symlink: Option<String>,
+    page_cache: Option<PageCache>,
+    fs: Weak<Iso9660FS>,
+    this: Weak<Iso9660Inode>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InodeMeta {
+    size: usize,
+    mode: InodeMode,
+    nlinks: usize,
+    uid: Uid,
+    gid: Gid,
+    atime: Duration,
+    mtime: Duration,
+    ctime: Duration,
+    rdev: u64,
+}
+
+impl Iso9660Inode {
+    pub(super) fn new_root(fs: &Arc<Iso9660FS>, record: &DirRecord) -> Result<Arc<Self>> {
+        // The attributes of the root directory are in its `.` record.
+        let rock_ridge = match fs.format() {
+            TreeFormat::RockRidge { skip } => {
+                let block = fs.read_block(record.extent)?;
+                match DirRecord::parse(&block)? {
+                    Some((dot, _)) => Some(fs.parse_rock_ridge(&dot, skip)?),
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+        let entry = DirEntry {
+            name: String::from("/"),
+            ino: record.extent as u64 * BLOCK_SIZE as u64,
+            type_: InodeType::Dir,
+            record: record.clone(),
+            extents: vec![(record.extent, record.data_len)],
+            rock_ridge,
+        };
+        Ok(Self::new(fs, &entry, None))
+    }
+
+    fn new(fs: &Arc<Iso9660FS>, entry: &DirEntry, parent: Option<Arc<Self>>) -> Arc<Self> {
+        let options = fs.options();
+        let size = entry
+            .extents
+            .iter()
+            .map(|&(_, len)| len as usize)
+            .sum::<usize>();
+        let recorded = entry.record.recorded;
+
+        let mut meta = InodeMeta {
+            size,
+            mode: if entry.type_ == InodeType::Dir {
+                options.dmode
+            } else {
+                options.mode
+            },
+            nlinks: if entry.type_ == InodeType::Dir { 2 } else { 1 },
+            uid: options.uid,
+            gid: options.gid,
+            atime: recorded,
+            mtime: recorded,
+            ctime: recorded,
+            rdev: 0,
+        };
+        let mut symlink = None;
+
+        if let Some(rock_ridge) = &entry.rock_ridge {
+            if let Some((mode, nlinks, uid, gid)) = rock_ridge.posix {
+                meta.mode = InodeMode::from_bits_truncate(mode as u16);
+                meta.nlinks = nlinks as usize;
+                meta.uid = Uid::new(uid);
+                meta.gid = Gid::new(gid);
+            }
+            meta.atime = rock_ridge.atime.unwrap_or(meta.atime);
+            meta.mtime = rock_ridge.mtime.unwrap_or(meta.mtime);
+            meta.ctime = rock_ridge.ctime.unwrap_or(meta.ctime);
+            if let Some((high, low)) = rock_ridge.rdev {
+                // Like Linux, the old 16-bit device numbers are stored in the low halves.
+                let device_id = if high == 0 {
+                    DeviceId::new((low >> 8) & 0xFF, low & 0xFF)
+                } else {
+                    DeviceId::new(high, low)
+                };
+                meta.rdev = device_id.into();
+            }
+            if entry.type_ == InodeType::SymLink {
+                let target = rock_ridge.symlink.clone().unwrap_or_default();
+                meta.size = target.len();
+                symlink = Some(target);
+            }
+        }
+
+        Arc::new_cyclic(|weak_self: &Weak<Self>| Self {
+            ino: entry.ino,
+            type_: entry.type_,
+            meta,
+            extents: entry.extents.clone(),
+            parent,
+            entries: Once::new(),
+            symlink,
+            page_cache: (entry.type_ == InodeType::File)
+                .then(|| PageCache::with_capacity(size, weak_self.clone() as _).unwrap()),
+            fs: Arc::downgrade(fs),
+            this: weak_self.clone(),
+        })
+    }
+
+    fn fs(&self) -> Arc<Iso9660FS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn parent(&self) -> Arc<Self> {
+        match &self.parent {
+            Some(parent) => parent.clone(),
+            None => self.fs().root().clone(),
+        }
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<&[DirEntry]> {
+        if let Some(entries) = self.entries.get() {
+            return Ok(entries);
+        }
+        let (extent, size) = self.extents[0];
+        let entries = self.fs().read_dir(extent, size as usize)?;
+        Ok(self.entries.call_once(|| entries))
+    }
+
+    fn find_entry(&self, name: &str) -> Result<Option<&DirEntry>> {
+        let fs = self.fs();
+        // The plain names are mapped to lower case, so they are looked up case-insensitively.
+        let is_case_insensitive =
+            fs.format() == TreeFormat::Plain && fs.options().map == NameMap::Normal;
+        let entry = self.entries()?.iter().find(|entry| {
+            if is_case_insensitive {
+                entry.name.eq_ignore_ascii_case(name)
+            } else {
+                entry.name == name
+            }
+        });
+        Ok(entry)
+    }
+
+    fn child_inode(&self, entry: &DirEntry) -> Arc<Self> {
+        let fs = self.fs();
+        if let Some(inode) = fs.find_inode(entry.ino) {
+            return inode;
+        }
+
+        let inode = Self::new(&fs, entry, Some(self.this()));
+        fs.insert_inode(entry.ino, inode.clone());
+        inode
+    }
+
+    /// Returns the device runs of the page `idx`, each of which is the offset within the page,
+    /// the offset on the device, and the length.
+    fn device_runs(&self, idx: usize) -> Vec<(usize, usize, usize)> {
+        let page_start = idx * PAGE_SIZE;
+        let page_end = (page_start + PAGE_SIZE).min(self.meta.size);
+
+        let mut runs = Vec::new();
+        let mut extent_start = 0;
+        for &(block, len) in self.extents.iter() {
+            let extent_end = extent_start + len as usize;
+            let start = page_start.max(extent_start);
+            let end = page_end.min(extent_end);
+            if start < end {
+                let device_offset = block as usize * BLOCK_SIZE + (start - extent_start);
+                runs.push((start - page_start, device_offset, end - start));
+            }
+            extent_start = extent_end;
+        }
+        runs
+    }
+}
+
+impl PageCacheBackend for Iso9660Inode {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let fs = self.fs();
+        let runs = self.device_runs(idx);
+
+        if let [(0, device_offset, PAGE_SIZE)] = runs.as_slice() {
+            let bio_segment = BioSegment::new_from_segment(
+                Segment::from(frame.clone()).into(),
+                BioDirection::FromDevice,
+            );
+            let bio = Bio::new(
+                BioType::Read,
+                Sid::from_offset(*device_offset),
+                vec![bio_segment],
+                None,
+            );
+            return Ok(bio.submit(fs.block_device().as_ref())?);
+        }
+
+        // The runs that are smaller than a page are read synchronously, since a bio segment
+        // cannot cover a part of a frame.
+        frame.writer().fill(0u8);
+        for (page_offset, device_offset, len) in runs {
+            let mut writer = frame.writer();
+            writer.skip(page_offset).limit(len);
+            fs.block_device()
+                .read(device_offset, &mut writer.to_fallible())?;
+        }
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page_async(&self, _idx: usize, _frame: &CachePage) -> Result<BioWaiter> {
+        // The pages are never dirty, since the files cannot be written.
+        return_errno!(Errno::EROFS)
+    }
+
+    fn npages(&self) -> usize {
+        self.meta.size.align_up(PAGE_SIZE) / PAGE_SIZE
+    }
+}
+
+impl Inode for Iso9660Inode {
+    fn size(&self) -> usize {
+        self.meta.size
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let meta = &self.meta;
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: meta.size,
+            blk_size: BLOCK_SIZE,
+            blocks: meta.size.div_ceil(512),
+            atime: meta.atime,
+            mtime: meta.mtime,
+            ctime: meta.ctime,
+            type_: self.type_,
+            mode: meta.mode,
+            nlinks: meta.nlinks,
+            uid: meta.uid,
+            gid: meta.gid,
+            rdev: meta.rdev,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.meta.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.meta.uid)
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.meta.gid)
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn atime(&self) -> Duration {
+        self.meta.atime
+    }
+
+    // The timestamps are never updated, like a `noatime` mount.
+    fn set_atime(&self, _time: Duration) {}
+
+    fn mtime(&self) -> Duration {
+        self.meta.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.meta.ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            if self.type_ == InodeType::Dir {
+                return_errno!(Errno::EISDIR);
+            }
+            return_errno!(Errno::EINVAL);
+        };
+
+        let (read_offset, read_len) = {
+            let size = self.meta.size;
+            let start = size.min(offset);
+            let end = size.min(offset.saturating_add(writer.avail()));
+            (start, end - start)
+        };
+        page_cache
+            .pages()
+            .read(read_offset, writer.limit(read_len))?;
+
+        Ok(read_len)
+    }
+
+    // The data are always cached, so direct I/O falls back to buffered I/O.
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn write_direct_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode, _type_: MknodType) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        if !self.type_.is_device() {
+            return None;
+        }
+        crate::device::get_device(self.meta.rdev as usize).ok()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            if *offset == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, 0)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", self.parent().ino, InodeType::Dir, 1)?;
+                *offset += 1;
+            }
+
+            let entries = self.entries()?;
+            for entry in entries.iter().skip(*offset - NUM_SPECIAL_ENTRIES) {
+                visitor.visit(&entry.name, entry.ino, entry.type_, *offset)?;
+                *offset += 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        if is_dot(name) {
+            return Ok(self.this());
+        }
+        if name == ".." {
+            return Ok(self.parent());
+        }
+
+        let entry = self
+            .find_entry(name)?
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        Ok(self.child_inode(entry))
+    }
+
+    fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.symlink {
+            Some(target) => Ok(target.clone()),
+            None => return_errno_with_message!(Errno::EINVAL, "the inode is not a symlink"),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+}
+
+impl Debug for Iso9660Inode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Iso9660Inode")
+            .field("ino", &self.ino)
+            .field("type_", &self.type_)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,416 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ISO 9660 file system, which is used by CD/DVD images and installer media.
+//!
+//! The volume is always read-only. Hybrid images, which also have an MBR or a GPT for USB
+//! sticks, are supported since the system area before the volume descriptors is not parsed.
+//!
+//! The features are as follows:
+//! 1. The Rock Ridge extensions, which record the POSIX attributes, the long names, the
+//!    symlinks, the device numbers, the timestamps, and the relocated deep directories.
+//! 2. The Joliet extensions, which record the long names in UCS-2. Like Linux, they are
+//!    used only if there are no Rock Ridge extensions.
+//! 3. The files that are larger than 4 GiB, which are recorded in multiple extents.
+//! 4. The mount options of Linux's isofs, i.e., `norock`, `nojoliet`, `uid`, `gid`, `mode`,
+//!    `dmode`, and `map`.
+//!
+//! # Limitation
+//!
+//! Here we summarizes the features that need to be implemented in the future.
+//! 1. Supports the multi-session volumes, where the last session should be mounted.
+//! 2. Supports the zisofs compressed files.
+//! 3. Supports the logical blocks whose sizes are not 2048 bytes.
+//! 4. Supports the character sets other than UTF-8 for the names.
+
+pub use fs::{Iso9660FS, Iso9660MountOptions};
+pub use inode::Iso9660Inode;
+
+mod dirent;
+mod fs;
+mod inode;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        fs::utils::{FileSystem, Inode, InodeMode, InodeType, MemoryDisk},
+        prelude::*,
+        process::{Gid, Uid},
+    };
+
+    const BLOCK_SIZE: usize = 2048;
+    const NUM_BLOCKS: usize = 28;
+
+    const PVD_BLOCK: u32 = 16;
+    const ROOT_BLOCK: u32 = 19;
+    const JOLIET_ROOT_BLOCK: u32 = 20;
+    const DIR_BLOCK: u32 = 21;
+    const JOLIET_DIR_BLOCK: u32 = 22;
+    const HELLO_BLOCK: u32 = 23;
+    const BIG_BLOCK: u32 = 24;
+    const NESTED_BLOCK: u32 = 27;
+
+    const HELLO: &[u8] = b"Hello, ISO!";
+    const NESTED: &[u8] = b"nested";
+    /// The size of `big.bin`, which is split into two extents and crosses a page.
+    const BIG_SIZE: usize = 5000;
+
+    /// The recording time of all the records, which is 2024-10-16 12:00:00 UTC.
+    const RECORDED: [u8; 7] = [124, 10, 16, 12, 0, 0, 0];
+    const RECORDED_SECS: u64 = 1_729_080_000;
+
+    const FLAG_DIR: u8 = 1 << 1;
+    const FLAG_MULTI_EXTENT: u8 = 1 << 7;
+
+    fn both_endian_u32(value: u32) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&value.to_le_bytes());
+        bytes[4..].copy_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    fn record(extent: u32, len: u32, flags: u8, name: &[u8], system_use: &[u8]) -> Vec<u8> {
+        let mut record = vec![0u8; 33];
+        record[2..10].copy_from_slice(&both_endian_u32(extent));
+        record[10..18].copy_from_slice(&both_endian_u32(len));
+        record[18..25].copy_from_slice(&RECORDED);
+        record[25] = flags;
+        record[32] = name.len() as u8;
+        record.extend_from_slice(name);
+        if record.len() % 2 == 1 {
+            record.push(0);
+        }
+        record.extend_from_slice(system_use);
+        if record.len() % 2 == 1 {
+            record.push(0);
+        }
+        record[0] = record.len() as u8;
+        record
+    }
+
+    fn directory(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut block: Vec<u8> = records.concat();
+        block.resize(BLOCK_SIZE, 0);
+        block
+    }
+
+    fn descriptor(type_: u8, root_extent: u32, escape: &[u8]) -> Vec<u8> {
+        let mut descriptor = vec![0u8; BLOCK_SIZE];
+        descriptor[0] = type_;
+        descriptor[1..6].copy_from_slice(b"CD001");
+        descriptor[6] = 1;
+        descriptor[80..88].copy_from_slice(&both_endian_u32(NUM_BLOCKS as u32));
+        descriptor[88..88 + escape.len()].copy_from_slice(escape);
+        descriptor[128..130].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        descriptor[130..132].copy_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+        let root = record(root_extent, BLOCK_SIZE as u32, FLAG_DIR, &[0], &[]);
+        descriptor[156..156 + root.len()].copy_from_slice(&root);
+        descriptor
+    }
+
+    fn ucs2(name: &str) -> Vec<u8> {
+        name.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    fn susp(signature: &[u8; 2], data: &[u8]) -> Vec<u8> {
+        let mut entry = vec![signature[0], signature[1], (data.len() + 4) as u8, 1];
+        entry.extend_from_slice(data);
+        entry
+    }
+
+    fn px(mode: u32, nlinks: u32, uid: u32, gid: u32) -> Vec<u8> {
+        let data: Vec<u8> = [mode, nlinks, uid, gid]
+            .into_iter()
+            .flat_map(both_endian_u32)
+            .collect();
+        susp(b"PX", &data)
+    }
+
+    fn nm(name: &str) -> Vec<u8> {
+        susp(b"NM", &[&[0], name.as_bytes()].concat())
+    }
+
+    fn sl(components: &[&str]) -> Vec<u8> {
+        let mut data = vec![0u8];
+        for component in components {
+            data.extend_from_slice(&[0, component.len() as u8]);
+            data.extend_from_slice(component.as_bytes());
+        }
+        susp(b"SL", &data)
+    }
+
+    /// Creates an image with a directory, a small file, a multi-extent file, and a nested file.
+    fn new_image(rock_ridge: bool, joliet: bool) -> Vec<u8> {
+        let mut image = vec![0u8; NUM_BLOCKS * BLOCK_SIZE];
+        let mut put = |block: u32, bytes: &[u8]| {
+            let offset = block as usize * BLOCK_SIZE;
+            image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        let rr = |entries: &[Vec<u8>]| -> Vec<u8> {
+            if rock_ridge {
+                entries.concat()
+            } else {
+                Vec::new()
+            }
+        };
+
+        put(PVD_BLOCK, &descriptor(1, ROOT_BLOCK, &[]));
+        if joliet {
+            put(PVD_BLOCK + 1, &descriptor(2, JOLIET_ROOT_BLOCK, b"%/E"));
+            put(PVD_BLOCK + 2, &descriptor(255, 0, &[]));
+        } else {
+            put(PVD_BLOCK + 1, &descriptor(255, 0, &[]));
+        }
+
+        let dir_len = BLOCK_SIZE as u32;
+        let big_first = BLOCK_SIZE as u32;
+        let big_second = (BIG_SIZE - BLOCK_SIZE) as u32;
+        put(
+            ROOT_BLOCK,
+            &directory(&[
+                record(
+                    ROOT_BLOCK,
+                    dir_len,
+                    FLAG_DIR,
+                    &[0],
+                    &rr(&[susp(b"SP", &[0xBE, 0xEF, 0]), px(0o40755, 3, 0, 0)]),
+                ),
+                record(ROOT_BLOCK, dir_len, FLAG_DIR, &[1], &[]),
+                record(
+                    BIG_BLOCK,
+                    big_first,
+                    FLAG_MULTI_EXTENT,
+                    b"BIG.BIN;1",
+                    &rr(&[nm("big.bin"), px(0o100644, 1, 0, 0)]),
+                ),
+                record(
+                    BIG_BLOCK + 1,
+                    big_second,
+                    0,
+                    b"BIG.BIN;1",
+                    &rr(&[nm("big.bin"), px(0o100644, 1, 0, 0)]),
+                ),
+                record(
+                    DIR_BLOCK,
+                    dir_len,
+                    FLAG_DIR,
+                    b"DIR",
+                    &rr(&[nm("dir"), px(0o40700, 2, 1000, 100)]),
+                ),
+                record(
+                    HELLO_BLOCK,
+                    HELLO.len() as u32,
+                    0,
+                    b"HELLO.TXT;1",
+                    &rr(&[nm("Hello, World.txt"), px(0o100640, 1, 1000, 100)]),
+                ),
+                record(
+                    0,
+                    0,
+                    0,
+                    b"LINK.;1",
+                    &rr(&[nm("link"), px(0o120777, 1, 0, 0), sl(&["dir", "file.txt"])]),
+                ),
+            ]),
+        );
+        put(
+            DIR_BLOCK,
+            &directory(&[
+                record(DIR_BLOCK, dir_len, FLAG_DIR, &[0], &[]),
+                record(ROOT_BLOCK, dir_len, FLAG_DIR, &[1], &[]),
+                record(
+                    NESTED_BLOCK,
+                    NESTED.len() as u32,
+                    0,
+                    b"FILE.TXT;1",
+                    &rr(&[nm("file.txt"), px(0o100644, 1, 0, 0)]),
+                ),
+            ]),
+        );
+
+        put(
+            JOLIET_ROOT_BLOCK,
+            &directory(&[
+                record(JOLIET_ROOT_BLOCK, dir_len, FLAG_DIR, &[0], &[]),
+                record(JOLIET_ROOT_BLOCK, dir_len, FLAG_DIR, &[1], &[]),
+                record(
+                    JOLIET_DIR_BLOCK,
+                    dir_len,
+                    FLAG_DIR,
+                    &ucs2("Joliet Dir"),
+                    &[],
+                ),
+                record(
+                    HELLO_BLOCK,
+                    HELLO.len() as u32,
+                    0,
+                    &ucs2("Hello Joliet.txt;1"),
+                    &[],
+                ),
+            ]),
+        );
+        put(
+            JOLIET_DIR_BLOCK,
+            &directory(&[
+                record(JOLIET_DIR_BLOCK, dir_len, FLAG_DIR, &[0], &[]),
+                record(JOLIET_ROOT_BLOCK, dir_len, FLAG_DIR, &[1], &[]),
+                record(
+                    NESTED_BLOCK,
+                    NESTED.len() as u32,
+                    0,
+                    &ucs2("Nested File.txt;1"),
+                    &[],
+                ),
+            ]),
+        );
+
+        put(HELLO_BLOCK, HELLO);
+        let big: Vec<u8> = (0..BIG_SIZE).map(|i| (i % 251) as u8).collect();
+        put(BIG_BLOCK, &big);
+        put(NESTED_BLOCK, NESTED);
+
+        image
+    }
+
+    fn open(image: &[u8], options: &str) -> Arc<Iso9660FS> {
+        let disk = MemoryDisk::from_image(image);
+        Iso9660FS::open(disk, Iso9660MountOptions::parse(options).unwrap()).unwrap()
+    }
+
+    fn list(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names = Vec::new();
+        dir.readdir_at(0, &mut names).unwrap();
+        names
+    }
+
+    fn read_all(file: &Arc<dyn Inode>) -> Vec<u8> {
+        let mut buf = vec![0u8; file.size()];
+        file.read_bytes_at(0, &mut buf).unwrap();
+        buf
+    }
+
+    #[ktest]
+    fn plain_names() {
+        let fs = open(&new_image(false, false), "");
+        let root = fs.root_inode();
+        assert_eq!(
+            list(&root),
+            vec![".", "..", "big.bin", "dir", "hello.txt", "link"]
+        );
+
+        // The plain names are looked up case-insensitively.
+        let hello = root.lookup("HELLO.TXT").unwrap();
+        assert_eq!(read_all(&hello), HELLO);
+        assert_eq!(hello.mode().unwrap().bits(), 0o444);
+        assert_eq!(hello.mtime().as_secs(), RECORDED_SECS);
+
+        let big = root.lookup("big.bin").unwrap();
+        assert_eq!(big.size(), BIG_SIZE);
+        let data = read_all(&big);
+        assert!(data
+            .iter()
+            .enumerate()
+            .all(|(i, &byte)| byte == (i % 251) as u8));
+
+        let dir = root.lookup("dir").unwrap();
+        assert_eq!(dir.type_(), InodeType::Dir);
+        assert_eq!(dir.mode().unwrap().bits(), 0o555);
+        assert_eq!(read_all(&dir.lookup("file.txt").unwrap()), NESTED);
+        assert_eq!(dir.lookup("..").unwrap().ino(), root.ino());
+
+        // The volume is read-only.
+        let mode = InodeMode::from_bits_truncate(0o644);
+        assert_eq!(
+            root.create("new", InodeType::File, mode)
+                .unwrap_err()
+                .error(),
+            Errno::EROFS
+        );
+        assert_eq!(root.unlink("hello.txt").unwrap_err().error(), Errno::EROFS);
+        assert_eq!(
+            hello.write_bytes_at(0, b"data").unwrap_err().error(),
+            Errno::EROFS
+        );
+        assert_eq!(hello.resize(0).unwrap_err().error(), Errno::EROFS);
+    }
+
+    #[ktest]
+    fn rock_ridge() {
+        // Rock Ridge is preferred to Joliet.
+        let fs = open(&new_image(true, true), "");
+        let root = fs.root_inode();
+        assert_eq!(
+            list(&root),
+            vec![".", "..", "big.bin", "dir", "Hello, World.txt", "link"]
+        );
+        assert_eq!(root.mode().unwrap().bits(), 0o755);
+        assert!(root.lookup("hello, world.txt").is_err());
+
+        let hello = root.lookup("Hello, World.txt").unwrap();
+        assert_eq!(read_all(&hello), HELLO);
+        assert_eq!(hello.mode().unwrap().bits(), 0o640);
+        assert_eq!(hello.metadata().uid, Uid::new(1000));
+        assert_eq!(hello.metadata().gid, Gid::new(100));
+
+        let dir = root.lookup("dir").unwrap();
+        assert_eq!(dir.mode().unwrap().bits(), 0o700);
+        assert_eq!(dir.metadata().nlinks, 2);
+        // The directories are identified by their extents.
+        assert_eq!(dir.ino(), DIR_BLOCK as u64 * BLOCK_SIZE as u64);
+
+        let link = root.lookup("link").unwrap();
+        assert_eq!(link.type_(), InodeType::SymLink);
+        assert_eq!(link.read_link().unwrap(), "dir/file.txt");
+
+        // The Rock Ridge entries can be ignored.
+        let fs = open(&new_image(true, false), "norock");
+        assert!(fs.root_inode().lookup("hello.txt").is_ok());
+    }
+
+    #[ktest]
+    fn joliet() {
+        let image = new_image(false, true);
+        let fs = open(&image, "");
+        let root = fs.root_inode();
+        assert_eq!(
+            list(&root),
+            vec![".", "..", "Joliet Dir", "Hello Joliet.txt"]
+        );
+        let dir = root.lookup("Joliet Dir").unwrap();
+        assert_eq!(read_all(&dir.lookup("Nested File.txt").unwrap()), NESTED);
+
+        // The Joliet names can be ignored.
+        let fs = open(&image, "nojoliet");
+        assert!(fs.root_inode().lookup("hello.txt").is_ok());
+    }
+
+    #[ktest]
+    fn mount_options() {
+        let fs = open(
+            &new_image(false, false),
+            "uid=1000,gid=100,mode=0640,dmode=0750",
+        );
+        let root = fs.root_inode();
+        let hello = root.lookup("hello.txt").unwrap();
+        assert_eq!(hello.metadata().uid, Uid::new(1000));
+        assert_eq!(hello.metadata().gid, Gid::new(100));
+        assert_eq!(hello.mode().unwrap().bits(), 0o640);
+        assert_eq!(root.mode().unwrap().bits(), 0o750);
+        assert_eq!(fs.sb().blocks, NUM_BLOCKS);
+
+        // The names are kept as they are without the mapping.
+        let fs = open(&new_image(false, false), "map=off");
+        assert!(list(&fs.root_inode())
+            .iter()
+            .any(|name| name == "HELLO.TXT;1"));
+
+        for options in ["map=acorn", "mode=999", "iocharset=iso8859-1", "unknown"] {
+            assert_eq!(
+                Iso9660MountOptions::parse(options).unwrap_err().error(),
+                Errno::EINVAL
+            );
+        }
+    }
+}
@@ -2,23 +2,32 @@
 
 pub mod device;
 pub mod devpts;
+pub mod devtmpfs;
 pub mod epoll;
 pub mod exfat;
 pub mod ext2;
 pub mod file_handle;
 pub mod file_table;
 pub mod fs_resolver;
+pub mod fuse;
 pub mod inode_handle;
+pub mod io_uring;
+pub mod iso9660;
 pub mod named_pipe;
+pub mod notify;
 pub mod overlayfs;
 pub mod path;
 pub mod pipe;
 pub mod procfs;
+pub mod quota;
 pub mod ramfs;
 pub mod rootfs;
+pub mod squashfs;
 pub mod sysfs;
 pub mod thread_info;
 pub mod utils;
+pub mod v9fs;
+pub mod vfat;
 
 use aster_block::BlockDevice;
 use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
@@ -34,15 +43,34 @@ use crate::{
 
 fn start_block_device(device_name: &str) -> Result<Arc<dyn BlockDevice>> {
     if let Some(device) = aster_block::get_device(device_name) {
-        let cloned_device = device.clone();
-        let task_fn = move || {
-            info!("spawn the virt-io-block thread");
-            let virtio_block_device = cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
-            loop {
-                virtio_block_device.handle_requests();
-            }
-        };
-        crate::ThreadOptions::new(task_fn).spawn();
+        let nr_queues = device
+            .downcast_ref::<VirtIoBlockDevice>()
+            .unwrap()
+            .nr_queues();
+        // Each thread is bound to a different CPU, so that it submits the requests to a
+        // different virtqueue.
+        for cpu in ostd::cpu::all_cpus().take(nr_queues) {
+            let cloned_device = device.clone();
+            let task_fn = move || {
+                info!("spawn the virt-io-block thread");
+                let virtio_block_device =
+                    cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
+                loop {
+                    virtio_block_device.handle_requests();
+                }
+            };
+            crate::ThreadOptions::new(task_fn)
+                .cpu_affinity(cpu.into())
+                .spawn();
+        }
+        // The partition table can only be read after the thread is spawned to handle the
+        // requests.
+        if let Err(err) = crate::device::scan_partitions(device_name) {
+            warn!(
+                "failed to scan the partitions of {}: {:?}",
+                device_name, err
+            );
+        }
         Ok(device)
     } else {
         return_errno_with_message!(Errno::ENOENT, "Device does not exist")
@@ -67,4 +95,26 @@ pub fn lazy_init() {
         println!("[kernel] Mount ExFat fs at {:?} ", target_path);
         self::rootfs::mount_fs_at(exfat_fs, &target_path).unwrap();
     }
+
+    // The NVMe namespaces serve the requests without dedicated threads.
+    for namespace in aster_nvme::all_namespaces() {
+        if let Err(err) = crate::device::scan_partitions(namespace.name()) {
+            warn!(
+                "failed to scan the partitions of {}: {:?}",
+                namespace.name(),
+                err
+            );
+        }
+    }
+
+    // The USB mass storage devices transfer the data in the context of the submitters.
+    for storage in aster_usb::all_storage_devices() {
+        if let Err(err) = crate::device::scan_partitions(storage.name()) {
+            warn!(
+                "failed to scan the partitions of {}: {:?}",
+                storage.name(),
+                err
+            );
+        }
+    }
 }
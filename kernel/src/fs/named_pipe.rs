@@ -31,9 +31,8 @@ impl NamedPipe {
 }
 
 impl Pollable for NamedPipe {
-    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
-        warn!("Named pipe doesn't support poll now, return IoEvents::empty for now.");
-        IoEvents::empty()
+    fn poll(&self, mask: IoEvents, mut poller: Option<&mut PollHandle>) -> IoEvents {
+        self.reader.poll(mask, poller.as_deref_mut()) | self.writer.poll(mask, poller)
     }
 }
 
@@ -0,0 +1,613 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The fanotify groups, which report the file system events to userspace.
+//!
+//! Reading a fanotify file returns the events, each of which comes with a new file descriptor of
+//! the file where the event occurs. For permission events, the operation is blocked until the
+//! listener writes a response with the file descriptor to allow or deny it.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man7/fanotify.7.html>.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::{sync::WaitQueue, task::Task};
+
+use super::{FsnotifyEvents, FsnotifyGroup, FsnotifyMarks, FsnotifyObject};
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        file_table::{FdFlags, FileDesc},
+        inode_handle::InodeHandle,
+        path::Dentry,
+        utils::{AccessMode, InodeMode, Metadata, StatusFlags},
+    },
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+};
+
+bitflags! {
+    /// The flags of `fanotify_init`.
+    pub struct FanotifyInitFlags: u32 {
+        const FAN_CLOEXEC = 0x1;
+        const FAN_NONBLOCK = 0x2;
+        /// The group receives the permission events after the file content is final.
+        const FAN_CLASS_CONTENT = 0x4;
+        /// The group receives the permission events before the file content is final.
+        const FAN_CLASS_PRE_CONTENT = 0x8;
+        const FAN_UNLIMITED_QUEUE = 0x10;
+        const FAN_UNLIMITED_MARKS = 0x20;
+        /// The thread IDs instead of the process IDs are reported.
+        const FAN_REPORT_TID = 0x100;
+    }
+}
+
+/// The maximum number of the queued events, which is the same as Linux.
+const MAX_QUEUED_EVENTS: usize = 16384;
+/// The maximum number of the marks of a group, which is the same as Linux.
+const MAX_MARKS: usize = 8192;
+
+const FANOTIFY_METADATA_VERSION: u8 = 3;
+/// The file descriptor of the events without files, e.g., the overflow event.
+const FAN_NOFD: FileDesc = -1;
+
+const FAN_ALLOW: u32 = 0x1;
+const FAN_DENY: u32 = 0x2;
+/// Audits the response, which is ignored since there is no audit subsystem.
+const FAN_AUDIT: u32 = 0x10;
+
+/// A fanotify file, which is created by `fanotify_init`.
+pub struct FanotifyFile {
+    group: Arc<FanotifyGroup>,
+    is_nonblocking: AtomicBool,
+}
+
+struct FanotifyGroup {
+    flags: FanotifyInitFlags,
+    /// The access mode of the file descriptors in the events.
+    event_access_mode: AccessMode,
+    /// The status flags of the file descriptors in the events.
+    event_status_flags: StatusFlags,
+    queue: Mutex<EventQueue>,
+    /// The permission events that are read but not responded, indexed by their file descriptors.
+    pending_requests: Mutex<BTreeMap<FileDesc, Arc<PermissionRequest>>>,
+    /// The objects where the group has marks.
+    marked_objects: Mutex<Vec<(FsnotifyObject, Arc<FsnotifyMarks>)>>,
+    pollee: Pollee,
+    /// The wait queue that is woken up when the permission events are responded.
+    response_wait_queue: WaitQueue,
+    weak_self: Weak<Self>,
+}
+
+struct EventQueue {
+    events: VecDeque<FanotifyEvent>,
+    /// Whether the file is closed, after which no events are queued.
+    is_released: bool,
+}
+
+struct FanotifyEvent {
+    mask: FsnotifyEvents,
+    /// The file where the event occurs, which is `None` for the overflow event.
+    dentry: Option<Dentry>,
+    pid: i32,
+    /// The request of the permission event.
+    request: Option<Arc<PermissionRequest>>,
+}
+
+/// A permission event waiting for the response.
+struct PermissionRequest {
+    state: SpinLock<PermissionState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PermissionState {
+    Pending,
+    Allowed,
+    Denied,
+    /// The operation is interrupted before the response.
+    Cancelled,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct FanotifyEventMetadata {
+    event_len: u32,
+    vers: u8,
+    reserved: u8,
+    metadata_len: u16,
+    mask: u64,
+    fd: i32,
+    pid: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct FanotifyResponse {
+    fd: i32,
+    response: u32,
+}
+
+impl FanotifyFile {
+    /// Creates a fanotify file.
+    ///
+    /// The file descriptors in the events are opened with `event_flags`.
+    pub fn new(flags: FanotifyInitFlags, event_flags: u32) -> Result<Self> {
+        let event_access_mode = AccessMode::from_u32(event_flags)?;
+        let event_status_flags = StatusFlags::from_bits_truncate(event_flags);
+        if event_status_flags.intersects(StatusFlags::O_DIRECT | StatusFlags::O_PATH) {
+            return_errno_with_message!(Errno::EINVAL, "the event file flags are invalid");
+        }
+
+        let group = Arc::new_cyclic(|weak_self| FanotifyGroup {
+            flags,
+            event_access_mode,
+            event_status_flags,
+            queue: Mutex::new(EventQueue {
+                events: VecDeque::new(),
+                is_released: false,
+            }),
+            pending_requests: Mutex::new(BTreeMap::new()),
+            marked_objects: Mutex::new(Vec::new()),
+            pollee: Pollee::new(),
+            response_wait_queue: WaitQueue::new(),
+            weak_self: weak_self.clone(),
+        });
+
+        Ok(Self {
+            group,
+            is_nonblocking: AtomicBool::new(flags.contains(FanotifyInitFlags::FAN_NONBLOCK)),
+        })
+    }
+
+    /// Returns whether the group can receive permission events.
+    pub fn can_receive_permission_events(&self) -> bool {
+        self.group.flags.intersects(
+            FanotifyInitFlags::FAN_CLASS_CONTENT | FanotifyInitFlags::FAN_CLASS_PRE_CONTENT,
+        )
+    }
+
+    /// Adds the events to the mark on the object.
+    ///
+    /// If `is_ignored` is true, the events are added to the ignored events.
+    pub fn add_mark(
+        &self,
+        object: FsnotifyObject,
+        events: FsnotifyEvents,
+        is_ignored: bool,
+        ignored_surv_modify: bool,
+    ) -> Result<()> {
+        let marks = object.marks()?;
+
+        let mut marked_objects = self.group.marked_objects.lock();
+        let is_marked = marked_objects
+            .iter()
+            .any(|(_, marked)| Arc::ptr_eq(marked, &marks));
+        if !is_marked
+            && !self
+                .group
+                .flags
+                .contains(FanotifyInitFlags::FAN_UNLIMITED_MARKS)
+            && marked_objects.len() >= MAX_MARKS
+        {
+            return_errno_with_message!(Errno::ENOSPC, "there are too many marks");
+        }
+
+        marks.add(
+            &self.group.weak_group(),
+            events,
+            is_ignored,
+            ignored_surv_modify,
+        );
+        if !is_marked {
+            marked_objects.push((object, marks));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the events from the mark on the object.
+    ///
+    /// If `is_ignored` is true, the events are removed from the ignored events.
+    pub fn remove_mark(
+        &self,
+        object: FsnotifyObject,
+        events: FsnotifyEvents,
+        is_ignored: bool,
+    ) -> Result<()> {
+        let marks = object.marks()?;
+
+        let mut marked_objects = self.group.marked_objects.lock();
+        if !marks.remove(&self.group.weak_group(), events, is_ignored)? {
+            marked_objects.retain(|(_, marked)| !Arc::ptr_eq(marked, &marks));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the marks on the objects that `filter` selects.
+    pub fn flush_marks(&self, filter: impl Fn(&FsnotifyObject) -> bool) {
+        let weak_group = self.group.weak_group();
+        self.group.marked_objects.lock().retain(|(object, marks)| {
+            if !filter(object) {
+                return true;
+            }
+            marks.remove_group(&weak_group);
+            false
+        });
+    }
+
+    /// Reads the events.
+    ///
+    /// The file descriptors in the events are installed to the file table of the current thread.
+    /// So the caller must not borrow the file table.
+    pub fn read_events(&self, writer: &mut VmWriter, ctx: &Context) -> Result<usize> {
+        const EVENT_LEN: usize = size_of::<FanotifyEventMetadata>();
+
+        if writer.avail() < EVENT_LEN {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for an event");
+        }
+
+        let mut read_len = 0;
+        while writer.avail() >= EVENT_LEN {
+            let event = if read_len > 0 {
+                match self.group.pop_event() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                }
+            } else if self.is_nonblocking.load(Ordering::Relaxed) {
+                self.group.pop_event()?
+            } else {
+                self.wait_events(IoEvents::IN, None, || self.group.pop_event())?
+            };
+
+            match self.group.copy_event_to_user(event, writer, ctx) {
+                Ok(()) => read_len += EVENT_LEN,
+                Err(err) if read_len == 0 => return Err(err),
+                Err(_) => break,
+            }
+        }
+
+        Ok(read_len)
+    }
+}
+
+impl Drop for FanotifyFile {
+    fn drop(&mut self) {
+        self.group.release();
+    }
+}
+
+impl Pollable for FanotifyFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.group
+            .pollee
+            .poll_with(mask, poller, || self.group.check_io_events())
+    }
+}
+
+impl FileLike for FanotifyFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        // The events are read by `read_events` because the file descriptors are installed.
+        return_errno_with_message!(Errno::EINVAL, "the events can only be read by read(2)");
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let write_len = size_of::<FanotifyResponse>();
+        if reader.remain() < write_len {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for a response");
+        }
+
+        let response = reader.read_val::<FanotifyResponse>()?;
+        let is_allowed = match response.response & !FAN_AUDIT {
+            FAN_ALLOW => true,
+            FAN_DENY => false,
+            _ => return_errno_with_message!(Errno::EINVAL, "the response is invalid"),
+        };
+        if response.fd < 0 {
+            return_errno_with_message!(Errno::EINVAL, "the file descriptor is invalid");
+        }
+
+        let Some(request) = self.group.pending_requests.lock().remove(&response.fd) else {
+            return_errno_with_message!(Errno::ENOENT, "no permission event is pending");
+        };
+        request.respond(is_allowed);
+        self.group.response_wait_queue.wake_all();
+
+        Ok(write_len)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking.load(Ordering::Relaxed) {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `FanotifyFile` to it.
+        Metadata::new_file(
+            0,
+            InodeMode::from_bits_truncate(0o600),
+            aster_block::BLOCK_SIZE,
+        )
+    }
+}
+
+impl FanotifyGroup {
+    fn weak_group(&self) -> Weak<dyn FsnotifyGroup> {
+        self.weak_self.clone() as Weak<dyn FsnotifyGroup>
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if self.queue.lock().events.is_empty() {
+            IoEvents::empty()
+        } else {
+            IoEvents::IN
+        }
+    }
+
+    /// Queues an event.
+    ///
+    /// Returns whether the event is queued.
+    fn push_event(&self, event: FanotifyEvent) -> bool {
+        let mut queue = self.queue.lock();
+        if queue.is_released {
+            return false;
+        }
+
+        // Merge the event into the last one if they only differ in the event types, which is the
+        // same as Linux. The permission events are never merged, since each needs a response.
+        if event.request.is_none() {
+            if let Some(last) = queue.events.back_mut() {
+                if last.request.is_none()
+                    && last.pid == event.pid
+                    && is_same_file(last.dentry.as_ref(), event.dentry.as_ref())
+                {
+                    last.mask |= event.mask;
+                    return true;
+                }
+            }
+        }
+
+        if queue.events.len() >= MAX_QUEUED_EVENTS
+            && !self.flags.contains(FanotifyInitFlags::FAN_UNLIMITED_QUEUE)
+        {
+            let has_overflowed = queue
+                .events
+                .back()
+                .is_some_and(|last| last.mask.contains(FsnotifyEvents::Q_OVERFLOW));
+            if !has_overflowed {
+                queue.events.push_back(FanotifyEvent {
+                    mask: FsnotifyEvents::Q_OVERFLOW,
+                    dentry: None,
+                    pid: 0,
+                    request: None,
+                });
+            }
+            return false;
+        }
+
+        queue.events.push_back(event);
+        drop(queue);
+        self.pollee.notify(IoEvents::IN);
+        true
+    }
+
+    fn pop_event(&self) -> Result<FanotifyEvent> {
+        let mut queue = self.queue.lock();
+        while let Some(event) = queue.events.pop_front() {
+            // The operations of the cancelled permission events are no longer waiting.
+            if event
+                .request
+                .as_ref()
+                .is_some_and(|request| request.state() == PermissionState::Cancelled)
+            {
+                continue;
+            }
+            if queue.events.is_empty() {
+                self.pollee.invalidate();
+            }
+            return Ok(event);
+        }
+
+        self.pollee.invalidate();
+        return_errno_with_message!(Errno::EAGAIN, "there are no events")
+    }
+
+    fn copy_event_to_user(
+        &self,
+        event: FanotifyEvent,
+        writer: &mut VmWriter,
+        ctx: &Context,
+    ) -> Result<()> {
+        let fd = match &event.dentry {
+            Some(dentry) => match self.open_event_file(dentry, ctx) {
+                Ok(fd) => fd,
+                Err(err) => {
+                    if let Some(request) = &event.request {
+                        request.respond(false);
+                        self.response_wait_queue.wake_all();
+                    }
+                    return Err(err);
+                }
+            },
+            None => FAN_NOFD,
+        };
+
+        let metadata = FanotifyEventMetadata {
+            event_len: size_of::<FanotifyEventMetadata>() as u32,
+            vers: FANOTIFY_METADATA_VERSION,
+            reserved: 0,
+            metadata_len: size_of::<FanotifyEventMetadata>() as u16,
+            mask: event.mask.bits(),
+            fd,
+            pid: event.pid,
+        };
+        if let Err(err) = writer.write_val(&metadata) {
+            if fd != FAN_NOFD {
+                let file_table = ctx.thread_local.borrow_file_table();
+                file_table.unwrap().write().close_file(fd);
+            }
+            if let Some(request) = &event.request {
+                request.respond(false);
+                self.response_wait_queue.wake_all();
+            }
+            return Err(err);
+        }
+
+        if let Some(request) = event.request {
+            self.pending_requests.lock().insert(fd, request);
+        }
+
+        Ok(())
+    }
+
+    fn open_event_file(&self, dentry: &Dentry, ctx: &Context) -> Result<FileDesc> {
+        // The file is opened without notifications, or the listener would receive the events of
+        // its own file.
+        let inode_handle = InodeHandle::new_without_fsnotify(
+            dentry.clone(),
+            self.event_access_mode,
+            self.event_status_flags,
+        )?;
+
+        let fd_flags = if self.flags.contains(FanotifyInitFlags::FAN_CLOEXEC) {
+            FdFlags::CLOEXEC
+        } else {
+            FdFlags::empty()
+        };
+        let file_table = ctx.thread_local.borrow_file_table();
+        let fd = file_table
+            .unwrap()
+            .write()
+            .insert(Arc::new(inode_handle), fd_flags);
+        Ok(fd)
+    }
+
+    /// Stops receiving events and allows all the permission events.
+    fn release(&self) {
+        let weak_group = self.weak_group();
+        for (_, marks) in self.marked_objects.lock().drain(..) {
+            marks.remove_group(&weak_group);
+        }
+
+        let events = {
+            let mut queue = self.queue.lock();
+            queue.is_released = true;
+            core::mem::take(&mut queue.events)
+        };
+        for request in events.into_iter().filter_map(|event| event.request) {
+            request.respond(true);
+        }
+        for (_, request) in core::mem::take(&mut *self.pending_requests.lock()) {
+            request.respond(true);
+        }
+        self.response_wait_queue.wake_all();
+    }
+}
+
+impl FsnotifyGroup for FanotifyGroup {
+    fn handle_event(&self, dentry: &Dentry, event: FsnotifyEvents) -> Result<()> {
+        let pid = Task::current()
+            .unwrap()
+            .as_posix_thread()
+            .map_or(0, |posix_thread| {
+                if self.flags.contains(FanotifyInitFlags::FAN_REPORT_TID) {
+                    posix_thread.tid()
+                } else {
+                    posix_thread.process().pid()
+                }
+            }) as i32;
+
+        if !event.intersects(FsnotifyEvents::PERM_EVENTS) {
+            self.push_event(FanotifyEvent {
+                mask: event,
+                dentry: Some(dentry.clone()),
+                pid,
+                request: None,
+            });
+            return Ok(());
+        }
+
+        let request = Arc::new(PermissionRequest::new());
+        let is_queued = self.push_event(FanotifyEvent {
+            mask: event,
+            dentry: Some(dentry.clone()),
+            pid,
+            request: Some(request.clone()),
+        });
+        // Like Linux, the operation is allowed if the event cannot be queued.
+        if !is_queued {
+            return Ok(());
+        }
+
+        let res = self
+            .response_wait_queue
+            .pause_until(|| match request.state() {
+                PermissionState::Pending => None,
+                state => Some(state),
+            });
+        match res {
+            Ok(PermissionState::Denied) => {
+                return_errno_with_message!(Errno::EPERM, "the operation is denied by fanotify")
+            }
+            Ok(_) => Ok(()),
+            Err(err) => {
+                request.cancel();
+                Err(err)
+            }
+        }
+    }
+}
+
+fn is_same_file(dentry: Option<&Dentry>, other: Option<&Dentry>) -> bool {
+    match (dentry, other) {
+        (Some(dentry), Some(other)) => {
+            Arc::ptr_eq(dentry.mount_node(), other.mount_node()) && dentry.key() == other.key()
+        }
+        _ => false,
+    }
+}
+
+impl PermissionRequest {
+    fn new() -> Self {
+        Self {
+            state: SpinLock::new(PermissionState::Pending),
+        }
+    }
+
+    fn state(&self) -> PermissionState {
+        *self.state.lock()
+    }
+
+    fn respond(&self, is_allowed: bool) {
+        let mut state = self.state.lock();
+        if *state == PermissionState::Pending {
+            *state = if is_allowed {
+                PermissionState::Allowed
+            } else {
+                PermissionState::Denied
+            };
+        }
+    }
+
+    fn cancel(&self) {
+        let mut state = self.state.lock();
+        if *state == PermissionState::Pending {
+            *state = PermissionState::Cancelled;
+        }
+    }
+}
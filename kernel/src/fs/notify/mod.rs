@@ -0,0 +1,352 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! File system notifications.
+//!
+//! The file operations (e.g., opening, reading, and modifying files) report events to the groups
+//! that watch the files. A group (e.g., a fanotify instance) watches the files by placing marks on
+//! their inodes, their mounts, or their file systems. A mark on a directory can also watch the
+//! files in the directory.
+//!
+//! Permission events are reported before the operations take place. The groups may block the
+//! operations until they decide, and the operations fail if any group denies them.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{
+    path::{Dentry, MountNode},
+    utils::{FileSystem, Inode, InodeType},
+};
+use crate::prelude::*;
+
+pub mod fanotify;
+
+bitflags! {
+    /// The events of file system notifications.
+    ///
+    /// The values are the same as the fanotify masks of Linux.
+    pub struct FsnotifyEvents: u64 {
+        /// The file is read.
+        const ACCESS = 0x1;
+        /// The file is written.
+        const MODIFY = 0x2;
+        /// The file that is opened for writing is closed.
+        const CLOSE_WRITE = 0x8;
+        /// The file that is not opened for writing is closed.
+        const CLOSE_NOWRITE = 0x10;
+        /// The file is opened.
+        const OPEN = 0x20;
+        /// The event queue of a group overflows.
+        const Q_OVERFLOW = 0x4000;
+        /// The file is about to be opened.
+        const OPEN_PERM = 0x10000;
+        /// The file is about to be read.
+        const ACCESS_PERM = 0x20000;
+        /// The events of the files in a directory are reported to the marks on the directory.
+        const EVENT_ON_CHILD = 0x0800_0000;
+        /// The events of directories are reported.
+        const ONDIR = 0x4000_0000;
+
+        const CLOSE = Self::CLOSE_WRITE.bits() | Self::CLOSE_NOWRITE.bits();
+        const PERM_EVENTS = Self::OPEN_PERM.bits() | Self::ACCESS_PERM.bits();
+    }
+}
+
+/// A group that receives the events of the files that it watches.
+pub trait FsnotifyGroup: Send + Sync {
+    /// Handles an event of a file.
+    ///
+    /// The event contains [`FsnotifyEvents::ONDIR`] if the file is a directory.
+    ///
+    /// For permission events, this method blocks until the group decides whether the operation is
+    /// allowed, and returns an error if the operation is denied.
+    fn handle_event(&self, dentry: &Dentry, event: FsnotifyEvents) -> Result<()>;
+}
+
+/// The number of the marks in the system.
+///
+/// If there are no marks, the events are not reported at all, so the file operations are not
+/// slowed down.
+static NR_MARKS: AtomicUsize = AtomicUsize::new(0);
+
+/// The marks on the file systems.
+static FS_MARKS: Mutex<Vec<(Weak<dyn FileSystem>, Arc<FsnotifyMarks>)>> = Mutex::new(Vec::new());
+
+/// An object that can be watched.
+#[derive(Clone)]
+pub enum FsnotifyObject {
+    Inode(Arc<dyn Inode>),
+    Mount(Arc<MountNode>),
+    FileSystem(Arc<dyn FileSystem>),
+}
+
+impl FsnotifyObject {
+    /// Returns the marks on the object.
+    ///
+    /// The marks are created if they do not exist.
+    pub fn marks(&self) -> Result<Arc<FsnotifyMarks>> {
+        match self {
+            Self::Inode(inode) => {
+                let Some(extension) = inode.extension() else {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the file system does not support notifications"
+                    );
+                };
+                Ok(extension.get_or_put_default::<FsnotifyMarks>())
+            }
+            Self::Mount(mount) => Ok(mount.fsnotify_marks().clone()),
+            Self::FileSystem(fs) => {
+                let mut fs_marks = FS_MARKS.lock();
+                fs_marks.retain(|(fs, _)| fs.strong_count() > 0);
+                if let Some((_, marks)) = fs_marks
+                    .iter()
+                    .find(|(marked_fs, _)| is_same_fs(marked_fs, fs))
+                {
+                    return Ok(marks.clone());
+                }
+                let marks = Arc::new(FsnotifyMarks::default());
+                fs_marks.push((Arc::downgrade(fs), marks.clone()));
+                Ok(marks)
+            }
+        }
+    }
+}
+
+fn is_same_fs(weak_fs: &Weak<dyn FileSystem>, fs: &Arc<dyn FileSystem>) -> bool {
+    core::ptr::addr_eq(weak_fs.as_ptr(), Arc::as_ptr(fs))
+}
+
+/// The marks of the groups on an object.
+#[derive(Default)]
+pub struct FsnotifyMarks {
+    marks: Mutex<Vec<Mark>>,
+}
+
+struct Mark {
+    group: Weak<dyn FsnotifyGroup>,
+    /// The events that the group watches.
+    mask: FsnotifyEvents,
+    /// The events that the group ignores, even if they are watched by other marks of the group.
+    ignored_mask: FsnotifyEvents,
+    /// Whether the ignored events are kept after the object is modified.
+    ignored_surv_modify: bool,
+}
+
+impl FsnotifyMarks {
+    /// Adds the events to the mark of the group.
+    ///
+    /// If `is_ignored` is true, the events are added to the ignored events. The mark is created
+    /// if it does not exist.
+    pub fn add(
+        &self,
+        group: &Weak<dyn FsnotifyGroup>,
+        events: FsnotifyEvents,
+        is_ignored: bool,
+        ignored_surv_modify: bool,
+    ) {
+        let mut marks = self.marks.lock();
+
+        let mark = match marks.iter().position(|mark| mark.group.ptr_eq(group)) {
+            Some(index) => &mut marks[index],
+            None => {
+                marks.push(Mark {
+                    group: group.clone(),
+                    mask: FsnotifyEvents::empty(),
+                    ignored_mask: FsnotifyEvents::empty(),
+                    ignored_surv_modify: false,
+                });
+                NR_MARKS.fetch_add(1, Ordering::Relaxed);
+                marks.last_mut().unwrap()
+            }
+        };
+
+        if is_ignored {
+            mark.ignored_mask |= events;
+            mark.ignored_surv_modify |= ignored_surv_modify;
+        } else {
+            mark.mask |= events;
+        }
+    }
+
+    /// Removes the events from the mark of the group.
+    ///
+    /// If `is_ignored` is true, the events are removed from the ignored events. The mark is
+    /// removed if it has no events left.
+    ///
+    /// Returns whether the mark still exists.
+    pub fn remove(
+        &self,
+        group: &Weak<dyn FsnotifyGroup>,
+        events: FsnotifyEvents,
+        is_ignored: bool,
+    ) -> Result<bool> {
+        let mut marks = self.marks.lock();
+
+        let Some(index) = marks.iter().position(|mark| mark.group.ptr_eq(group)) else {
+            return_errno_with_message!(Errno::ENOENT, "the mark does not exist");
+        };
+        let mark = &mut marks[index];
+        if is_ignored {
+            mark.ignored_mask -= events;
+        } else {
+            mark.mask -= events;
+        }
+
+        if !mark.mask.is_empty() || !mark.ignored_mask.is_empty() {
+            return Ok(true);
+        }
+        marks.swap_remove(index);
+        NR_MARKS.fetch_sub(1, Ordering::Relaxed);
+        Ok(false)
+    }
+
+    /// Removes the mark of the group.
+    pub fn remove_group(&self, group: &Weak<dyn FsnotifyGroup>) {
+        let mut marks = self.marks.lock();
+        let old_len = marks.len();
+        marks.retain(|mark| !mark.group.ptr_eq(group));
+        NR_MARKS.fetch_sub(old_len - marks.len(), Ordering::Relaxed);
+    }
+
+    /// Adds the interests of the groups in the event to `interests`.
+    fn collect_interests(
+        &self,
+        event: FsnotifyEvents,
+        is_parent: bool,
+        interests: &mut Vec<Interest>,
+    ) {
+        let mut marks = self.marks.lock();
+        for mark in marks.iter_mut() {
+            if event.contains(FsnotifyEvents::MODIFY) && !mark.ignored_surv_modify {
+                mark.ignored_mask = FsnotifyEvents::empty();
+            }
+            if is_parent && !mark.mask.contains(FsnotifyEvents::EVENT_ON_CHILD) {
+                continue;
+            }
+            let Some(group) = mark.group.upgrade() else {
+                continue;
+            };
+
+            match interests
+                .iter_mut()
+                .find(|interest| Arc::ptr_eq(&interest.group, &group))
+            {
+                Some(interest) => {
+                    interest.mask |= mark.mask;
+                    interest.ignored_mask |= mark.ignored_mask;
+                }
+                None => interests.push(Interest {
+                    group,
+                    mask: mark.mask,
+                    ignored_mask: mark.ignored_mask,
+                }),
+            }
+        }
+    }
+}
+
+impl Drop for FsnotifyMarks {
+    fn drop(&mut self) {
+        NR_MARKS.fetch_sub(self.marks.get_mut().len(), Ordering::Relaxed);
+    }
+}
+
+/// The merged marks of a group on the objects related to an event.
+struct Interest {
+    group: Arc<dyn FsnotifyGroup>,
+    mask: FsnotifyEvents,
+    ignored_mask: FsnotifyEvents,
+}
+
+/// Reports an event of the file to the groups that watch it.
+fn send_event(dentry: &Dentry, event: FsnotifyEvents) -> Result<()> {
+    if NR_MARKS.load(Ordering::Relaxed) == 0 {
+        return Ok(());
+    }
+
+    // Collect the interests before calling the groups, because the groups may block.
+    let mut interests = Vec::new();
+    if let Some(marks) = dentry
+        .inode()
+        .extension()
+        .and_then(|extension| extension.get::<FsnotifyMarks>())
+    {
+        marks.collect_interests(event, false, &mut interests);
+    }
+    // Like Linux, the parent directory does not cross the mounts.
+    if !dentry.is_root_of_mount() {
+        if let Some(marks) = dentry.effective_parent().and_then(|parent| {
+            parent
+                .inode()
+                .extension()
+                .and_then(|extension| extension.get::<FsnotifyMarks>())
+        }) {
+            marks.collect_interests(event, true, &mut interests);
+        }
+    }
+    dentry
+        .mount_node()
+        .fsnotify_marks()
+        .collect_interests(event, false, &mut interests);
+    let fs = dentry.fs();
+    let fs_marks = FS_MARKS
+        .lock()
+        .iter()
+        .find(|(marked_fs, _)| is_same_fs(marked_fs, &fs))
+        .map(|(_, marks)| marks.clone());
+    if let Some(marks) = fs_marks {
+        marks.collect_interests(event, false, &mut interests);
+    }
+
+    let is_dir = dentry.type_() == InodeType::Dir;
+    for interest in interests {
+        if is_dir && !interest.mask.contains(FsnotifyEvents::ONDIR) {
+            continue;
+        }
+        let mut events = event & (interest.mask - interest.ignored_mask);
+        if events.is_empty() {
+            continue;
+        }
+        if is_dir {
+            events |= FsnotifyEvents::ONDIR;
+        }
+        interest.group.handle_event(dentry, events)?;
+    }
+
+    Ok(())
+}
+
+/// Reports that the file is about to be opened.
+pub fn on_open_perm(dentry: &Dentry) -> Result<()> {
+    send_event(dentry, FsnotifyEvents::OPEN_PERM)
+}
+
+/// Reports that the file is opened.
+pub fn on_open(dentry: &Dentry) {
+    let _ = send_event(dentry, FsnotifyEvents::OPEN);
+}
+
+/// Reports that the file is about to be read.
+pub fn on_access_perm(dentry: &Dentry) -> Result<()> {
+    send_event(dentry, FsnotifyEvents::ACCESS_PERM)
+}
+
+/// Reports that the file is read.
+pub fn on_access(dentry: &Dentry) {
+    let _ = send_event(dentry, FsnotifyEvents::ACCESS);
+}
+
+/// Reports that the file is modified.
+pub fn on_modify(dentry: &Dentry) {
+    let _ = send_event(dentry, FsnotifyEvents::MODIFY);
+}
+
+/// Reports that the file is closed.
+pub fn on_close(dentry: &Dentry, is_writable: bool) {
+    let event = if is_writable {
+        FsnotifyEvents::CLOSE_WRITE
+    } else {
+        FsnotifyEvents::CLOSE_NOWRITE
+    };
+    let _ = send_event(dentry, event);
+}
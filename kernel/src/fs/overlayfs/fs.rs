@@ -4,6 +4,7 @@
 
 use alloc::format;
 use core::{
+    ops::Range,
     sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
@@ -16,13 +17,14 @@ use inherit_methods_macro::inherit_methods;
 use ostd::mm::{FrameAllocOptions, UntypedMem};
 
 use crate::{
+    device::Whiteout,
     fs::{
         device::Device,
         path::Dentry,
         utils::{
-            DirentVisitor, FallocMode, FileSystem, FsFlags, Inode, InodeMode, InodeType, IoctlCmd,
-            Metadata, MknodType, SuperBlock, XattrName, XattrNamespace, XattrSetFlags, NAME_MAX,
-            XATTR_VALUE_MAX_LEN,
+            DirentVisitor, FallocMode, FileSystem, FsFlags, Inode, InodeFlags, InodeMode,
+            InodeType, IoctlCmd, Metadata, MknodType, SuperBlock, XattrName, XattrNamespace,
+            XattrSetFlags, XATTR_VALUE_MAX_LEN,
         },
     },
     prelude::*,
@@ -46,8 +48,8 @@ pub struct OverlayFS {
     config: OverlayConfig,
     /// Super block.
     sb: OverlaySB,
-    /// Unique inode number generator.
-    next_ino: AtomicU64,
+    /// The root inode.
+    root: Arc<OverlayInode>,
     /// Weak self reference.
     self_: Weak<OverlayFS>,
 }
@@ -69,7 +71,11 @@ struct OverlayLower {
 /// the same file system as the upper layer.
 struct OverlayWork {
     dentry: Dentry,
-    // TODO: Align the work directory's behavior with Linux.
+    /// The `work` subdirectory, where the upper inodes are prepared
+    /// before they are moved into the upper layer.
+    dir: Arc<dyn Inode>,
+    /// The generator of the temporary names in `dir`.
+    next_temp: AtomicU64,
 }
 
 /// Provides an unified inode abstraction for its user, internal it
@@ -84,66 +90,111 @@ struct OverlayInode {
     /// The lock is intended to implement `rename`.
     name_upon_creation: SpinLock<String>,
     /// The parent inode. `None` for root inode.
-    parent: Option<Arc<OverlayInode>>,
+    /// The lock is intended to implement `rename`.
+    parent: SpinLock<Option<Arc<OverlayInode>>>,
     /// The mutable upper regular inode.
     upper: Mutex<Option<Arc<dyn Inode>>>,
     /// Whether the upper inode is an opaque directory.
     upper_is_opaque: bool,
-    /// The immutable lower layered regular inodes.
-    lowers: Vec<Arc<dyn Inode>>,
+    /// The immutable lower layered regular inodes, from top to bottom.
+    lowers: Vec<LayerInode>,
+    /// The children that are in use.
+    ///
+    /// A child is shared by all its users, so the copy-up and the rename
+    /// operations on it are visible to all of them.
+    children: Mutex<BTreeMap<String, Weak<OverlayInode>>>,
     /// Weak fs reference.
     fs: Weak<OverlayFS>,
     /// Weak self reference.
     self_: Weak<OverlayInode>,
 }
 
+/// A regular inode in a layer.
+#[derive(Clone)]
+struct LayerInode {
+    /// The index of the layer. The upper layer is 0, and the lower layers start from 1.
+    layer: LayerIdx,
+    inode: Arc<dyn Inode>,
+}
+
 impl OverlayFS {
     pub fn new(upper: Dentry, lower: Vec<Dentry>, work: Dentry) -> Result<Arc<Self>> {
+        if lower.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "at least one lower layer is required");
+        }
+        if lower.len() > LayerIdx::MAX as usize {
+            return_errno_with_message!(Errno::EINVAL, "too many lower layers");
+        }
+        if core::iter::once(&upper)
+            .chain(lower.iter())
+            .chain(core::iter::once(&work))
+            .any(|dentry| dentry.type_() != InodeType::Dir)
+        {
+            return_errno_with_message!(Errno::ENOTDIR, "the layers must be directories");
+        }
+        if !Arc::ptr_eq(&upper.fs(), &work.fs()) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the work directory must be in the same file system as the upper layer"
+            );
+        }
+        if upper.inode().ino() == work.inode().ino() {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the work directory must be different from the upper layer"
+            );
+        }
+
+        let root_upper = upper.inode().clone();
+        let root_ino = UniqueNoGenerator::gen_unique_ino(0, root_upper.ino())?;
+        let root_lowers = lower
+            .iter()
+            .enumerate()
+            .map(|(idx, dentry)| LayerInode {
+                layer: (idx + 1) as LayerIdx,
+                inode: dentry.inode().clone(),
+            })
+            .collect();
+        let work = OverlayWork::new(work)?;
+
         Ok(Arc::new_cyclic(|weak| Self {
             upper: OverlayUpper { dentry: upper },
             lower: OverlayLower { dentries: lower },
-            work: OverlayWork { dentry: work },
+            work,
             config: OverlayConfig::default(),
             sb: OverlaySB,
-            next_ino: AtomicU64::new(0),
+            root: Arc::new_cyclic(|weak_root| OverlayInode {
+                ino: root_ino,
+                type_: InodeType::Dir,
+                name_upon_creation: SpinLock::new(String::from("")),
+                parent: SpinLock::new(None),
+                upper: Mutex::new(Some(root_upper)),
+                upper_is_opaque: false,
+                lowers: root_lowers,
+                children: Mutex::new(BTreeMap::new()),
+                fs: weak.clone(),
+                self_: weak_root.clone(),
+            }),
             self_: weak.clone(),
         }))
     }
 }
 
 impl FileSystem for OverlayFS {
-    /// Utilizes the layered directory entries to build the root inode.
     fn root_inode(&self) -> Arc<dyn Inode> {
-        let fs = self.fs();
-        let upper_inode = fs.upper.dentry.inode().clone();
-        let ino = upper_inode.ino();
-        Arc::new_cyclic(|weak| OverlayInode {
-            ino,
-            type_: InodeType::Dir,
-            name_upon_creation: SpinLock::new(String::from("")),
-            parent: None,
-            upper: Mutex::new(Some(upper_inode)),
-            upper_is_opaque: false,
-            lowers: fs
-                .lower
-                .dentries
-                .iter()
-                .map(|dentry| dentry.inode())
-                .cloned()
-                .collect(),
-            fs: self.self_.clone(),
-            self_: weak.clone(),
-        })
+        self.root.clone()
     }
 
     fn sync(&self) -> Result<()> {
-        // TODO: Issue sync to all upper inodes.
-        Ok(())
+        // Only the upper layer can be modified.
+        self.upper.dentry.fs().sync()
     }
 
     fn sb(&self) -> SuperBlock {
-        // TODO: Fill the super block with valid field values.
-        SuperBlock::new(OVERLAY_FS_MAGIC, BLOCK_SIZE, NAME_MAX)
+        // Like Linux, the space usage is the one of the upper layer.
+        let mut sb = self.upper.dentry.fs().sb();
+        sb.magic = OVERLAY_FS_MAGIC;
+        sb
     }
 
     fn flags(&self) -> FsFlags {
@@ -151,14 +202,47 @@ impl FileSystem for OverlayFS {
     }
 }
 
-impl OverlayFS {
-    fn fs(&self) -> Arc<OverlayFS> {
-        self.self_.upgrade().unwrap()
+/// The name of the subdirectory of the work directory, which is the real work directory.
+const WORK_DIR_NAME: &str = "work";
+
+impl OverlayWork {
+    fn new(dentry: Dentry) -> Result<Self> {
+        let parent = dentry.inode();
+        // Like Linux, the leftovers of the previous mounts are removed.
+        let dir = match parent.lookup(WORK_DIR_NAME) {
+            Ok(dir) if dir.type_() == InodeType::Dir => {
+                remove_all_children(&dir)?;
+                dir
+            }
+            Ok(_) => {
+                parent.unlink(WORK_DIR_NAME)?;
+                parent.create(WORK_DIR_NAME, InodeType::Dir, InodeMode::empty())?
+            }
+            Err(e) if e.error() == Errno::ENOENT => {
+                parent.create(WORK_DIR_NAME, InodeType::Dir, InodeMode::empty())?
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            dentry,
+            dir,
+            next_temp: AtomicU64::new(0),
+        })
     }
 
-    /// Allocates a new unique inode number.
-    fn alloc_ino(&self) -> u64 {
-        self.next_ino.fetch_add(1, Ordering::Relaxed)
+    /// Allocates a new unique name for a temporary inode.
+    fn alloc_temp_name(&self) -> String {
+        format!("#{:x}", self.next_temp.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Removes a temporary inode after a failed operation.
+    fn remove_temp(&self, name: &str, type_: InodeType) {
+        let _ = if type_ == InodeType::Dir {
+            self.dir.rmdir(name)
+        } else {
+            self.dir.unlink(name)
+        };
     }
 }
 
@@ -177,63 +261,33 @@ impl OverlayInode {
     /// Creates a new non-exist child `OverlayInode` in the upper layer.
     /// If the parent directories do not exist, they will be created recursively in the upper layer.
     pub fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
-        if self.type_ != InodeType::Dir {
-            return_errno!(Errno::ENOTDIR);
-        }
-
-        // TODO: Hold the upper lock from here to avoid race condition
-        let is_whiteout = match self.lookup_inner(name) {
-            Ok(Some(_)) => return_errno!(Errno::EEXIST),
-            Ok(None) => true,
-            Err(e) => {
-                if e.error() != Errno::ENOENT {
-                    return Err(e);
-                } else {
-                    false
-                }
-            }
-        };
-
-        if !is_whiteout {
-            self.build_upper_recursively_if_needed()?;
-        }
-
-        // Protect the whole create operation
-        let upper_guard = self.upper.lock();
-        let upper = upper_guard.as_ref().unwrap();
+        self.create_child(name, type_, |upper| upper.create(name, type_, mode))
+    }
 
-        let mut upper_is_opaque = false;
-        if is_whiteout {
-            // Delete the whiteout file first then create the new file
-            // or the new opaque directory.
-            upper.unlink(&whiteout_name(name))?;
+    pub fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        let inode_type = type_.inode_type();
+        self.create_child(name, inode_type, |upper| upper.mknod(name, mode, type_))
+    }
 
-            if type_ == InodeType::Dir {
-                upper_is_opaque = true;
-            }
+    pub fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let old = old
+            .downcast_ref::<OverlayInode>()
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not same fs"))?;
+        if !Arc::ptr_eq(&self.overlay_fs(), &old.overlay_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
         }
-
-        let new_upper = upper.create(name, type_, mode)?;
-        if upper_is_opaque {
-            new_upper.set_xattr(
-                XattrName::try_from_full_name(OPAQUE_DIR_XATTR_NAME).unwrap(),
-                &mut VmReader::from(WHITEOUT_AND_OPAQUE_XATTR_VALUE.as_slice()).to_fallible(),
-                XattrSetFlags::CREATE_ONLY,
-            )?;
+        if old.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "old is a dir");
         }
 
-        let new_child = Arc::new_cyclic(|weak| OverlayInode {
-            ino: new_upper.ino(),
-            type_,
-            name_upon_creation: SpinLock::new(String::from(name)),
-            parent: Some(self.self_.upgrade().unwrap()),
-            upper: Mutex::new(Some(new_upper)),
-            upper_is_opaque,
-            lowers: Vec::new(),
-            fs: self.fs.clone(),
-            self_: weak.clone(),
-        });
-        Ok(new_child)
+        // Like Linux without the `index` feature, the link is made
+        // to the copied-up inode.
+        let old_upper = old.build_upper_recursively_if_needed()?;
+        self.create_child(name, old.type_, |upper| {
+            upper.link(&old_upper, name)?;
+            upper.lookup(name)
+        })?;
+        Ok(())
     }
 
     /// Writes data to the target inode, if it resides in the lower layer,
@@ -271,108 +325,75 @@ impl OverlayInode {
 
     /// Returns the children objects in a unified view.
     /// The object from the upper layer with the same name will mask the lower ones.
+    ///
+    /// The offsets are the positions in the unified view.
     pub fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
         if self.type_ != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
 
-        let overlay_dir_visitor = self.readdir_inner(offset)?;
+        // TODO: Cache the unified view in the opened file like Linux,
+        // instead of building it upon every call.
+        let entries = self.merged_entries()?;
 
-        for (offset, (name, ino, type_)) in overlay_dir_visitor.as_merged_view() {
-            visitor.visit(name, *ino, *type_, *offset)?;
+        let mut cur_offset = offset;
+        for entry in entries.iter().skip(offset) {
+            if let Err(e) = visitor.visit(&entry.name, entry.ino, entry.type_, cur_offset) {
+                if cur_offset == offset {
+                    return Err(e);
+                }
+                break;
+            }
+            cur_offset += 1;
         }
 
-        Ok(overlay_dir_visitor.cur_offset())
+        Ok(cur_offset - offset)
     }
 
-    /// Deletes the target file by creating a "whiteout" file from the upper layer.
+    /// Deletes the target file by creating a whiteout from the upper layer.
     /// The corresponding parent directories will be created also if they do not exist.
     pub fn unlink(&self, name: &str) -> Result<()> {
         // TODO: Hold the upper lock from here to avoid race condition
-        let inode = self.lookup(name)?;
-        let target = inode.downcast_ref::<OverlayInode>().unwrap();
-        if target.type_() == InodeType::Dir {
+        let target = self.lookup_inner(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        if target.type_ == InodeType::Dir {
             return_errno!(Errno::EISDIR);
         }
 
-        let mut upper_guard = self.upper.lock();
-        if upper_guard.is_none() {
-            drop(upper_guard);
-            self.build_upper_recursively_if_needed()?;
-            upper_guard = self.upper.lock();
-        }
-
-        let upper = upper_guard.as_ref().unwrap();
-        let target_has_valid_lower = target.has_valid_lower();
-        if target.has_valid_upper() {
-            upper.unlink(name)?;
+        let upper = self.build_upper_recursively_if_needed()?;
+        if self.lower_has_child(name)? {
+            // The whiteout replaces the upper file, if any.
+            self.create_whiteout(&upper, name)?;
         } else {
-            assert!(target_has_valid_lower);
-        }
-
-        if target_has_valid_lower {
-            let whiteout = upper.create(
-                &whiteout_name(name),
-                InodeType::File,
-                InodeMode::from_bits_truncate(0o644),
-            )?;
-            // FIXME: Align the whiteout xattr behavior with Linux
-            whiteout.set_xattr(
-                XattrName::try_from_full_name(WHITEOUT_XATTR_NAME).unwrap(),
-                &mut VmReader::from(WHITEOUT_AND_OPAQUE_XATTR_VALUE.as_slice()).to_fallible(),
-                XattrSetFlags::CREATE_ONLY,
-            )?;
+            upper.unlink(name)?;
         }
 
+        self.children.lock().remove(name);
         Ok(())
     }
 
-    /// Deletes the target directory by creating an "opaque" directory from the upper layer.
+    /// Deletes the target directory by creating a whiteout from the upper layer.
     /// The corresponding parent directories will be created also if they do not exist.
     pub fn rmdir(&self, name: &str) -> Result<()> {
         // TODO: Hold the upper lock from here to avoid race condition
-        let inode = self.lookup(name)?;
-        let target = inode.downcast_ref::<OverlayInode>().unwrap();
-        if target.type_() != InodeType::Dir {
+        let target = self.lookup_inner(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        if target.type_ != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
-
-        self.build_upper_recursively_if_needed()?;
-        let upper_guard = self.upper.lock();
-        let upper = upper_guard.as_ref().unwrap();
-
-        let visitor = target.readdir_inner(0)?;
-        if visitor.visited_files() > 0 {
+        if !target.is_empty_dir()? {
             return_errno!(Errno::ENOTEMPTY);
         }
 
-        // Delete all the whiteout files if necessary
-        if visitor.contains_whiteout() {
-            let target_upper = target.upper().unwrap();
-
-            let mut target_visitor = Vec::<String>::new();
-            target_upper.readdir_at(0, &mut target_visitor)?;
-
-            for whiteout in target_visitor.iter().skip(2) {
-                assert!(whiteout.starts_with(WHITEOUT_PREFIX));
-                target_upper.unlink(whiteout)?;
-            }
+        let upper = self.build_upper_recursively_if_needed()?;
+        if let Some(target_upper) = target.upper() {
+            // Only the whiteouts are left in the upper directory.
+            remove_all_children(&target_upper)?;
+            upper.rmdir(name)?;
+        }
+        if self.lower_has_child(name)? {
+            self.create_whiteout(&upper, name)?;
         }
 
-        upper.rmdir(name)?;
-
-        let whiteout = upper.create(
-            &whiteout_name(name),
-            InodeType::File,
-            InodeMode::from_bits_truncate(0o644),
-        )?;
-        // FIXME: Align the whiteout xattr behavior with Linux
-        whiteout.set_xattr(
-            XattrName::try_from_full_name(WHITEOUT_XATTR_NAME).unwrap(),
-            &mut VmReader::from(WHITEOUT_AND_OPAQUE_XATTR_VALUE.as_slice()).to_fallible(),
-            XattrSetFlags::CREATE_ONLY,
-        )?;
-
+        self.children.lock().remove(name);
         Ok(())
     }
 
@@ -414,22 +435,6 @@ impl OverlayInode {
         upper.page_cache()
     }
 
-    pub fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
-        if self.type_ != InodeType::Dir {
-            return_errno_with_message!(Errno::ENOTDIR, "not mknod on a dir");
-        }
-        let upper = self.build_upper_recursively_if_needed()?;
-        upper.mknod(name, mode, type_)
-    }
-
-    pub fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
-        if self.type_ != InodeType::Dir {
-            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
-        }
-        let upper = self.build_upper_recursively_if_needed()?;
-        upper.link(old, name)
-    }
-
     pub fn read_link(&self) -> Result<String> {
         if self.type_ != InodeType::SymLink {
             return_errno_with_message!(Errno::EINVAL, "self is not symlink");
@@ -445,13 +450,91 @@ impl OverlayInode {
         upper.write_link(target)
     }
 
-    pub fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
-        // TODO: Support the rename operation based on the `redirect_mode` feature,
-        // rename the upper only may unexpectedly reveal the lower inodes.
-        return_errno_with_message!(
-            Errno::EOPNOTSUPP,
-            "rename is not supported in current overlayfs"
-        );
+    /// Renames a child, which is copied up to the upper layer first.
+    ///
+    /// Like Linux without the `redirect_dir` feature, the directories that have lower
+    /// inodes cannot be renamed, and `EXDEV` is returned to let the user space fall back
+    /// to copying.
+    pub fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        let target = target
+            .downcast_ref::<OverlayInode>()
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not same fs"))?;
+        if !Arc::ptr_eq(&self.overlay_fs(), &target.overlay_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        if self.type_ != InodeType::Dir || target.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self or target is not dir");
+        }
+
+        let old = self
+            .lookup_inner(old_name)?
+            .ok_or(Error::new(Errno::ENOENT))?;
+        if self.ino == target.ino && old_name == new_name {
+            return Ok(());
+        }
+        let old_is_dir = old.type_ == InodeType::Dir;
+        if old_is_dir && old.has_valid_lower() {
+            return_errno_with_message!(
+                Errno::EXDEV,
+                "the merged or lower directory cannot be renamed"
+            );
+        }
+
+        let (new, new_is_whiteout) = match target.lookup_inner(new_name) {
+            Ok(Some(new)) => (Some(new), false),
+            Ok(None) => (None, true),
+            Err(e) if e.error() == Errno::ENOENT => (None, false),
+            Err(e) => return Err(e),
+        };
+        if let Some(new) = new.as_ref() {
+            match (old_is_dir, new.type_ == InodeType::Dir) {
+                (true, true) if !new.is_empty_dir()? => {
+                    return_errno_with_message!(Errno::ENOTEMPTY, "new is not empty");
+                }
+                (true, false) => return_errno_with_message!(Errno::ENOTDIR, "new is not dir"),
+                (false, true) => return_errno_with_message!(Errno::EISDIR, "new is dir"),
+                _ => {}
+            }
+        }
+
+        let old_upper = old.build_upper_recursively_if_needed()?;
+        let self_upper = self.build_upper_recursively_if_needed()?;
+        let target_upper = target.build_upper_recursively_if_needed()?;
+
+        if old_is_dir && new_is_whiteout {
+            // A directory cannot replace the whiteout.
+            target_upper.unlink(new_name)?;
+        }
+        if let Some(new_upper) = new.as_ref().and_then(|new| new.upper()) {
+            if new_upper.type_() == InodeType::Dir {
+                // Only the whiteouts are left in the upper directory.
+                remove_all_children(&new_upper)?;
+            }
+        }
+        if old_is_dir && target.has_valid_lower() {
+            // Hide the lower directories of the same name.
+            set_private_xattr(
+                &old_upper,
+                OPAQUE_DIR_XATTR_NAME,
+                &WHITEOUT_AND_OPAQUE_XATTR_VALUE,
+                XattrSetFlags::CREATE_OR_REPLACE,
+            )?;
+        }
+
+        let needs_whiteout = self.lower_has_child(old_name)?;
+        self_upper.rename(old_name, &target_upper, new_name)?;
+        if needs_whiteout {
+            self.create_whiteout(&self_upper, old_name)?;
+        }
+
+        self.children.lock().remove(old_name);
+        *old.name_upon_creation.lock() = String::from(new_name);
+        *old.parent.lock() = Some(target.this());
+        target
+            .children
+            .lock()
+            .insert(String::from(new_name), Arc::downgrade(&old));
+        Ok(())
     }
 
     pub fn sync_all(&self) -> Result<()> {
@@ -473,6 +556,59 @@ impl OverlayInode {
             |upper| upper.ioctl(cmd, arg),
         )
     }
+
+    pub fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
+        if is_private_xattr(name.full_name().as_bytes()) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the xattr is private to overlayfs");
+        }
+        self.get_top_valid_inode().get_xattr(name, value_writer)
+    }
+
+    pub fn set_xattr(
+        &self,
+        name: XattrName,
+        value_reader: &mut VmReader,
+        flags: XattrSetFlags,
+    ) -> Result<()> {
+        if is_private_xattr(name.full_name().as_bytes()) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the xattr is private to overlayfs");
+        }
+        let upper = self.build_upper_recursively_if_needed()?;
+        upper.set_xattr(name, value_reader, flags)
+    }
+
+    pub fn remove_xattr(&self, name: XattrName) -> Result<()> {
+        if is_private_xattr(name.full_name().as_bytes()) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the xattr is private to overlayfs");
+        }
+        let upper = self.build_upper_recursively_if_needed()?;
+        upper.remove_xattr(name)
+    }
+
+    /// Lists the xattrs, except the ones private to overlayfs.
+    pub fn list_xattr(
+        &self,
+        namespace: XattrNamespace,
+        list_writer: &mut VmWriter,
+    ) -> Result<usize> {
+        let list = read_xattr_list(&self.get_top_valid_inode(), namespace)?;
+        let list: Vec<u8> = list
+            .split_inclusive(|&byte| byte == 0)
+            .filter(|name| !is_private_xattr(name))
+            .flatten()
+            .copied()
+            .collect();
+
+        let list_avail_len = list_writer.avail();
+        if list_avail_len == 0 {
+            return Ok(list.len());
+        }
+        if list.len() > list_avail_len {
+            return_errno_with_message!(Errno::ERANGE, "the xattr list buffer is too small");
+        }
+        list_writer.write_fallible(&mut VmReader::from(list.as_slice()))?;
+        Ok(list.len())
+    }
 }
 
 #[inherit_methods(from = "self.get_top_valid_inode()")]
@@ -485,12 +621,8 @@ impl OverlayInode {
     pub fn mtime(&self) -> Duration;
     pub fn ctime(&self) -> Duration;
     pub fn as_device(&self) -> Option<Arc<dyn Device>>;
-    pub fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize>;
-    pub fn list_xattr(
-        &self,
-        namespace: XattrNamespace,
-        list_writer: &mut VmWriter,
-    ) -> Result<usize>;
+    pub fn next_data_extent(&self, offset: usize) -> Result<Option<Range<usize>>>;
+    pub fn flags(&self) -> InodeFlags;
 }
 
 #[inherit_methods(from = "self.build_upper_recursively_if_needed()?")]
@@ -500,11 +632,11 @@ impl OverlayInode {
     pub fn set_owner(&self, uid: Uid) -> Result<()>;
     pub fn set_group(&self, gid: Gid) -> Result<()>;
     pub fn fallocate(&self, mode: FallocMode, offset: usize, len: usize) -> Result<()>;
+    pub fn set_flags(&self, flags: InodeFlags) -> Result<()>;
 }
 
 #[inherit_methods(from = "self.build_upper_recursively_if_needed().unwrap()")]
 impl OverlayInode {
-    pub fn set_atime(&self, time: Duration);
     pub fn set_mtime(&self, time: Duration);
     pub fn set_ctime(&self, time: Duration);
 }
@@ -530,7 +662,7 @@ impl OverlayInode {
         // Note that the whiteout or opaque check is performed in `lookup` and `create`,
         // the only two places where an `OverlayInode` can be created.
         // So a lower inode can never be a whiteout file or opaque directory.
-        Some(&self.lowers[0])
+        Some(&self.lowers[0].inode)
     }
 
     fn has_valid_lower(&self) -> bool {
@@ -549,6 +681,16 @@ impl OverlayInode {
         self.upper.lock().clone()
     }
 
+    /// Sets the access time.
+    ///
+    /// Like Linux, updating the access time does not trigger copy-up, so the access time is only
+    /// set if the upper inode exists.
+    pub fn set_atime(&self, time: Duration) {
+        if let Some(upper) = self.upper() {
+            upper.set_atime(time);
+        }
+    }
+
     fn num_lowers(&self) -> usize {
         self.lowers.len()
     }
@@ -565,27 +707,79 @@ impl OverlayInode {
         self.fs.upgrade().unwrap()
     }
 
+    fn this(&self) -> Arc<OverlayInode> {
+        self.self_.upgrade().unwrap()
+    }
+
+    /// Builds a new child `OverlayInode` and puts it into the children in use.
+    ///
+    /// If another user has put a child of the same name, that child is returned instead.
+    fn new_child(
+        &self,
+        name: &str,
+        type_: InodeType,
+        upper: Option<Arc<dyn Inode>>,
+        upper_is_opaque: bool,
+        lowers: Vec<LayerInode>,
+    ) -> Result<Arc<OverlayInode>> {
+        // Like Linux, a copied-up inode keeps the inode number of its origin.
+        let ino = match upper.as_ref() {
+            Some(upper) => match origin_ino(upper)? {
+                Some(ino) => ino,
+                None => UniqueNoGenerator::gen_unique_ino(0, upper.ino())?,
+            },
+            None => UniqueNoGenerator::gen_unique_ino(lowers[0].layer, lowers[0].inode.ino())?,
+        };
+        let child = Arc::new_cyclic(|weak| OverlayInode {
+            ino,
+            type_,
+            name_upon_creation: SpinLock::new(String::from(name)),
+            parent: SpinLock::new(Some(self.this())),
+            upper: Mutex::new(upper),
+            upper_is_opaque,
+            lowers,
+            children: Mutex::new(BTreeMap::new()),
+            fs: self.fs.clone(),
+            self_: weak.clone(),
+        });
+
+        let mut children = self.children.lock();
+        if let Some(existing) = children.get(name).and_then(Weak::upgrade) {
+            return Ok(existing);
+        }
+        // Drop the children that are no longer in use. Doing this only
+        // when the size reaches a power of two keeps the cost amortized.
+        if children.len().is_power_of_two() {
+            children.retain(|_, child| child.strong_count() > 0);
+        }
+        children.insert(String::from(name), Arc::downgrade(&child));
+        Ok(child)
+    }
+
     /// Lookups the target regular inodes in a layered manner then
     /// builds the corresponding `OverlayInode`.
     /// The whiteout and opaque checks are performed here only.
-    fn lookup_inner(&self, name: &str) -> Result<Option<Arc<dyn Inode>>> {
+    fn lookup_inner(&self, name: &str) -> Result<Option<Arc<OverlayInode>>> {
         if self.type_ != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
 
+        if let Some(child) = self.children.lock().get(name).and_then(Weak::upgrade) {
+            return Ok(Some(child));
+        }
+
         let mut type_ = None;
         let mut upper_is_opaque = false;
         let mut upper_is_not_dir = false;
 
-        let upper_child = if let Some(upper) = self.upper.lock().as_ref() {
-            // First check whiteout then opaque
-            if upper.lookup(&whiteout_name(name)).is_ok() {
-                // Provide whiteout information for `create`
-                return Ok(None);
-            }
-
+        let upper_child = if let Some(upper) = self.upper() {
             match upper.lookup(name) {
                 Ok(child) => {
+                    if is_whiteout(&child) {
+                        // Provide whiteout information for `create`
+                        return Ok(None);
+                    }
+
                     let child_type = child.type_();
                     if child_type == InodeType::Dir {
                         upper_is_opaque = is_opaque_dir(&child)?;
@@ -613,25 +807,26 @@ impl OverlayInode {
         } else {
             let mut children = Vec::new();
             for lower in &self.lowers {
-                if lower.lookup(&whiteout_name(name)).is_ok() {
-                    break;
-                }
+                if let Ok(child) = lower.inode.lookup(name) {
+                    if is_whiteout(&child) {
+                        break;
+                    }
 
-                if let Ok(child) = lower.lookup(name) {
                     let child_type = child.type_();
                     let is_child_opaque = child_type == InodeType::Dir && is_opaque_dir(&child)?;
 
                     if upper_child.is_none() && children.is_empty() {
-                        children.push(child);
                         let _ = type_.insert(child_type);
                     } else {
                         let type_ = type_.unwrap();
                         if type_ != InodeType::Dir || type_ != child_type {
                             break;
-                        } else {
-                            children.push(child);
                         }
                     }
+                    children.push(LayerInode {
+                        layer: lower.layer,
+                        inode: child,
+                    });
 
                     if is_child_opaque {
                         break;
@@ -645,78 +840,151 @@ impl OverlayInode {
             return_errno!(Errno::ENOENT);
         }
 
-        let ino = if let Some(upper) = &upper_child {
-            UniqueNoGenerator::gen_unique_ino(0 as LayerIdx, upper.ino())?
-        } else {
-            UniqueNoGenerator::gen_unique_ino(1 as LayerIdx, lower_children[0].ino())?
-        };
-        let child_ovl_inode = Arc::new_cyclic(|weak| OverlayInode {
-            ino,
-            type_: type_.unwrap(),
-            name_upon_creation: SpinLock::new(String::from(name)),
-            parent: Some(self.self_.upgrade().unwrap()),
-            upper: Mutex::new(upper_child),
+        let child = self.new_child(
+            name,
+            type_.unwrap(),
+            upper_child,
             upper_is_opaque,
-            lowers: lower_children,
-            fs: self.fs.clone(),
-            self_: weak.clone(),
-        });
-
-        Ok(Some(child_ovl_inode))
+            lower_children,
+        )?;
+        Ok(Some(child))
     }
 
-    fn readdir_inner(&self, offset: usize) -> Result<OverlayDirVisitor> {
-        let mut overlay_visitor = OverlayDirVisitor::new();
-        let (mut layer_idx, fs_offset) = UniqueNoGenerator::parse_unique_offset(offset);
-
-        // Process all the potential whiteout files before `layer_idx`
-        if layer_idx > 0 {
-            overlay_visitor.set_whiteout_only_mode();
+    /// Creates a new child in the upper layer with `create_upper`.
+    fn create_child<F>(
+        &self,
+        name: &str,
+        type_: InodeType,
+        create_upper: F,
+    ) -> Result<Arc<dyn Inode>>
+    where
+        F: FnOnce(&Arc<dyn Inode>) -> Result<Arc<dyn Inode>>,
+    {
+        if self.type_ != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
 
-            let upper = self.upper();
-            for idx in 0..=layer_idx {
-                let cur_inode = if idx == 0 {
-                    upper.as_ref()
+        // TODO: Hold the upper lock from here to avoid race condition
+        let is_whiteout = match self.lookup_inner(name) {
+            Ok(Some(_)) => return_errno!(Errno::EEXIST),
+            Ok(None) => true,
+            Err(e) => {
+                if e.error() != Errno::ENOENT {
+                    return Err(e);
                 } else {
-                    self.lowers.get(idx as usize - 1)
-                };
-
-                if let Some(cur_inode) = cur_inode {
-                    cur_inode.readdir_at(0, &mut overlay_visitor)?;
+                    false
                 }
             }
+        };
 
-            overlay_visitor.unset_whiteout_only_mode();
-        }
+        self.build_upper_recursively_if_needed()?;
+
+        // Protect the whole create operation
+        let upper_guard = self.upper.lock();
+        let upper = upper_guard.as_ref().unwrap();
+
+        let mut upper_is_opaque = false;
+        if is_whiteout {
+            // Delete the whiteout file first then create the new file
+            // or the new opaque directory.
+            upper.unlink(name)?;
 
-        // Process all files from `layer_idx` and `fs_offset`
-        if layer_idx == 0 {
-            if let Some(upper) = self.upper() {
-                debug_assert!(upper.type_() == InodeType::Dir);
-                upper.readdir_at(fs_offset, &mut overlay_visitor)?;
+            if type_ == InodeType::Dir {
+                upper_is_opaque = true;
             }
+        }
 
-            layer_idx += 1;
-            overlay_visitor.set_cur_layer(layer_idx);
+        let new_upper = create_upper(upper)?;
+        if upper_is_opaque {
+            set_private_xattr(
+                &new_upper,
+                OPAQUE_DIR_XATTR_NAME,
+                &WHITEOUT_AND_OPAQUE_XATTR_VALUE,
+                XattrSetFlags::CREATE_ONLY,
+            )?;
         }
+        drop(upper_guard);
 
-        if !self.is_opaque_dir() && layer_idx > 0 && layer_idx as usize <= self.lowers.len() {
-            // TODO: Figure out how to check the opaque directories within lower layers.
-            let first_lower = &self.lowers[layer_idx as usize - 1];
-            first_lower.readdir_at(fs_offset, &mut overlay_visitor)?;
+        let new_child =
+            self.new_child(name, type_, Some(new_upper), upper_is_opaque, Vec::new())?;
+        Ok(new_child)
+    }
 
-            layer_idx += 1;
-            overlay_visitor.set_cur_layer(layer_idx);
+    /// Creates a whiteout of the name in the upper directory, which replaces
+    /// the existing non-directory inode of the name, if any.
+    fn create_whiteout(&self, upper: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let fs = self.overlay_fs();
+        let work = &fs.work;
+        let temp_name = work.alloc_temp_name();
 
-            for lower in self.lowers.iter().skip(layer_idx as usize - 1) {
-                lower.readdir_at(0, &mut overlay_visitor)?;
+        // Like Linux, the whiteout is a character device with the device number 0/0.
+        work.dir.mknod(
+            &temp_name,
+            InodeMode::empty(),
+            MknodType::CharDeviceNode(Arc::new(Whiteout)),
+        )?;
+        work.dir
+            .rename(&temp_name, upper, name)
+            .inspect_err(|_| work.remove_temp(&temp_name, InodeType::CharDevice))
+    }
+
+    /// Returns whether a child of the name exists in the lower layers.
+    fn lower_has_child(&self, name: &str) -> Result<bool> {
+        for lower in &self.lowers {
+            match lower.inode.lookup(name) {
+                Ok(child) => return Ok(!is_whiteout(&child)),
+                Err(e) if e.error() == Errno::ENOENT => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+
+    fn is_empty_dir(&self) -> Result<bool> {
+        Ok(self.merged_entries()?.len() == NUM_SPECIAL_ENTRIES)
+    }
+
+    /// Returns the entries of the directory in a unified view, starting with `.` and `..`.
+    ///
+    /// The entries in the upper layers mask the ones of the same names in the lower layers,
+    /// and the whiteouts are hidden. The inode numbers are the ones returned by `lookup`.
+    fn merged_entries(&self) -> Result<Vec<OverlayDirEntry>> {
+        let parent_ino = self
+            .parent
+            .lock()
+            .as_ref()
+            .map_or(self.ino, |parent| parent.ino);
+        let mut entries = vec![
+            OverlayDirEntry::new(String::from("."), self.ino, InodeType::Dir),
+            OverlayDirEntry::new(String::from(".."), parent_ino, InodeType::Dir),
+        ];
+        let mut visited_names = HashSet::new();
+
+        let upper = self.upper().map(|inode| LayerInode { layer: 0, inode });
+        for dir in upper.into_iter().chain(self.lowers.iter().cloned()) {
+            for (name, fs_ino, type_) in read_dir_entries(&dir.inode)? {
+                if !visited_names.insert(name.clone()) {
+                    continue;
+                }
+                if type_ == InodeType::CharDevice && is_whiteout(&dir.inode.lookup(&name)?) {
+                    continue;
+                }
 
-                layer_idx += 1;
-                overlay_visitor.set_cur_layer(layer_idx);
+                // Only the upper inodes in the merged directories can be copied up.
+                let origin_ino = if dir.layer == 0 && self.has_valid_lower() {
+                    origin_ino(&dir.inode.lookup(&name)?)?
+                } else {
+                    None
+                };
+                let ino = match origin_ino {
+                    Some(ino) => ino,
+                    None => UniqueNoGenerator::gen_unique_ino(dir.layer, fs_ino)?,
+                };
+                entries.push(OverlayDirEntry::new(name, ino, type_));
             }
         }
 
-        Ok(overlay_visitor)
+        Ok(entries)
     }
 
     fn build_upper_recursively_if_needed(&self) -> Result<Arc<dyn Inode>> {
@@ -725,49 +993,79 @@ impl OverlayInode {
             return Ok(upper.clone());
         }
 
-        debug_assert!(!self.parent.is_none());
+        // There must exist a valid lower inode if the upper is missing
+        assert!(!self.lowers.is_empty());
+        // The root inode always has the upper inode.
+        let parent = self.parent.lock().clone().unwrap();
         // FIXME: Should we hold every upper locks from lower to upper
         // for such a long period?
-        let parent_upper = self
-            .parent
-            .as_ref()
-            .unwrap()
-            .build_upper_recursively_if_needed()?;
-
-        let mode = self.get_top_valid_lower_inode().unwrap().mode()?;
-        let new_upper = parent_upper.create(&self.name_upon_creation(), self.type_, mode)?;
+        let parent_upper = parent.build_upper_recursively_if_needed()?;
 
-        // There must exist a valid lower inode if the upper is missing
-        assert!(!self.lowers.is_empty());
-        self.do_copy_up(&new_upper)?;
+        let new_upper = self.do_copy_up(&parent_upper)?;
 
         let _ = upper_guard.insert(new_upper.clone());
         Ok(new_upper)
     }
 
-    /// Do the "copy-up" operation for the given upper inode.
-    fn do_copy_up(&self, upper_inode: &Arc<dyn Inode>) -> Result<()> {
-        if self.lowers.is_empty() {
-            return Ok(());
-        }
-        let Some(lower_inode) = self.get_top_valid_lower_inode() else {
-            return Ok(());
+    /// Do the "copy-up" operation, which copies the top valid lower inode
+    /// to the upper layer.
+    ///
+    /// Like Linux, the upper inode is prepared in the work directory, then
+    /// moved into the upper layer, so a partial copy is never visible.
+    fn do_copy_up(&self, parent_upper: &Arc<dyn Inode>) -> Result<Arc<dyn Inode>> {
+        let lower_inode = self.get_top_valid_lower_inode().unwrap();
+        let fs = self.overlay_fs();
+        let work = &fs.work;
+        let temp_name = work.alloc_temp_name();
+
+        let mode = lower_inode.mode()?;
+        let upper_inode = match self.type_ {
+            InodeType::File | InodeType::Dir | InodeType::SymLink => {
+                work.dir.create(&temp_name, self.type_, mode)?
+            }
+            InodeType::NamedPipe => work.dir.mknod(&temp_name, mode, MknodType::NamedPipeNode)?,
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                let device = lower_inode.as_device().ok_or_else(|| {
+                    Error::with_message(Errno::EOPNOTSUPP, "the device is not supported")
+                })?;
+                work.dir.mknod(&temp_name, mode, device.into())?
+            }
+            InodeType::Socket => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the socket cannot be copied up")
+            }
         };
 
-        let upper_type = upper_inode.type_();
-        if upper_type != lower_inode.type_() {
-            return Ok(());
-        }
-
-        // First copy the metadata, then the data, finally the xattr
-        Self::copy_up_metadata(lower_inode, upper_inode)?;
+        let copy_up = || -> Result<()> {
+            // First copy the data, then the xattr, finally the metadata,
+            // which is changed by copying the data
+            match self.type_ {
+                InodeType::File => Self::copy_up_data(lower_inode, &upper_inode)?,
+                InodeType::SymLink => upper_inode.write_link(&lower_inode.read_link()?)?,
+                _ => {}
+            }
+            Self::copy_up_xattr(lower_inode, &upper_inode)?;
+            match set_private_xattr(
+                &upper_inode,
+                ORIGIN_XATTR_NAME,
+                &self.ino.to_le_bytes(),
+                XattrSetFlags::CREATE_OR_REPLACE,
+            ) {
+                // The inode number changes after remounting if the upper layer
+                // does not support xattrs.
+                Err(e) if e.error() == Errno::EOPNOTSUPP => {}
+                result => result?,
+            }
+            Self::copy_up_metadata(lower_inode, &upper_inode)?;
 
-        if upper_type == InodeType::File {
-            Self::copy_up_data(lower_inode, upper_inode)?;
+            work.dir
+                .rename(&temp_name, parent_upper, &self.name_upon_creation())
+        };
+        if let Err(e) = copy_up() {
+            work.remove_temp(&temp_name, self.type_);
+            return Err(e);
         }
 
-        Self::copy_up_xattr(lower_inode, upper_inode)?;
-        Ok(())
+        Ok(upper_inode)
     }
 
     fn copy_up_metadata(lower: &Arc<dyn Inode>, upper: &Arc<dyn Inode>) -> Result<()> {
@@ -805,18 +1103,11 @@ impl OverlayInode {
     fn copy_up_xattr(lower: &Arc<dyn Inode>, upper: &Arc<dyn Inode>) -> Result<()> {
         debug_assert!(lower.type_() == upper.type_());
 
-        let list_len = lower.list_xattr(
-            XattrNamespace::Trusted,
-            &mut VmWriter::from([].as_mut_slice()).to_fallible(),
-        )?;
-        if list_len == 0 {
+        // Listing the trusted xattrs shows the xattrs in all namespaces.
+        let list = read_xattr_list(lower, XattrNamespace::Trusted)?;
+        if list.is_empty() {
             return Ok(());
         }
-        let mut list = vec![0u8; list_len];
-        lower.list_xattr(
-            XattrNamespace::Trusted,
-            &mut VmWriter::from(list.as_mut_slice()).to_fallible(),
-        )?;
 
         let value_buf = FrameAllocOptions::new()
             .zeroed(false)
@@ -828,6 +1119,10 @@ impl OverlayInode {
             if name.is_empty() {
                 break;
             }
+            // The private xattrs describe the lower inode itself.
+            if is_private_xattr(name.as_bytes()) {
+                continue;
+            }
             let value_len = lower.get_xattr(
                 XattrName::try_from_full_name(name.as_ref()).unwrap(),
                 &mut value_buf.writer().to_fallible(),
@@ -843,35 +1138,102 @@ impl OverlayInode {
     }
 }
 
-const WHITEOUT_XATTR_NAME: &str = "trusted.overlay.whiteout";
+const OVERLAY_XATTR_PREFIX: &str = "trusted.overlay.";
 const OPAQUE_DIR_XATTR_NAME: &str = "trusted.overlay.opaque";
 const WHITEOUT_AND_OPAQUE_XATTR_VALUE: [u8; 1] = [121u8]; // "y", represents the xattr is set
+/// The xattr that records the inode number of the origin of a copied-up inode.
+const ORIGIN_XATTR_NAME: &str = "trusted.overlay.origin";
+
+/// The number of the special entries (`.` and `..`) in a directory.
+const NUM_SPECIAL_ENTRIES: usize = 2;
 
-const WHITEOUT_PREFIX: &str = ".wh.";
-const WHITEOUT_PREFIX_SIZE: usize = WHITEOUT_PREFIX.len();
+fn is_whiteout(inode: &Arc<dyn Inode>) -> bool {
+    inode.type_() == InodeType::CharDevice && inode.metadata().rdev == 0
+}
 
-fn whiteout_name(name: &str) -> String {
-    format!("{}{}", WHITEOUT_PREFIX, name)
+fn is_private_xattr(name: &[u8]) -> bool {
+    name.starts_with(OVERLAY_XATTR_PREFIX.as_bytes())
 }
 
 fn is_opaque_dir(inode: &Arc<dyn Inode>) -> Result<bool> {
     assert_eq!(inode.type_(), InodeType::Dir);
 
-    let name = XattrName::try_from_full_name(OPAQUE_DIR_XATTR_NAME).unwrap();
     let mut value = [0u8];
-    match inode.get_xattr(
-        name,
-        &mut VmWriter::from(value.as_mut_slice()).to_fallible(),
-    ) {
+    let is_set = get_private_xattr(inode, OPAQUE_DIR_XATTR_NAME, &mut value)?.is_some();
+    Ok(is_set && value == WHITEOUT_AND_OPAQUE_XATTR_VALUE)
+}
+
+/// Returns the inode number of the origin of a copied-up inode.
+fn origin_ino(inode: &Arc<dyn Inode>) -> Result<Option<u64>> {
+    let mut value = [0u8; size_of::<u64>()];
+    let value_len = get_private_xattr(inode, ORIGIN_XATTR_NAME, &mut value)?;
+    Ok((value_len == Some(value.len())).then_some(u64::from_le_bytes(value)))
+}
+
+/// Gets the private xattr of the regular inode, returning `None` if it is absent.
+fn get_private_xattr(
+    inode: &Arc<dyn Inode>,
+    name: &str,
+    value: &mut [u8],
+) -> Result<Option<usize>> {
+    let name = XattrName::try_from_full_name(name).unwrap();
+    match inode.get_xattr(name, &mut VmWriter::from(value).to_fallible()) {
+        Ok(value_len) => Ok(Some(value_len)),
         Err(e) => match e.error() {
-            Errno::E2BIG | Errno::ENODATA | Errno::EOPNOTSUPP | Errno::ERANGE => {
-                return Ok(false);
-            }
-            _ => return Err(e),
+            Errno::E2BIG | Errno::ENODATA | Errno::EOPNOTSUPP | Errno::ERANGE => Ok(None),
+            _ => Err(e),
         },
-        Ok(_) => {}
-    };
-    Ok(value == WHITEOUT_AND_OPAQUE_XATTR_VALUE)
+    }
+}
+
+fn set_private_xattr(
+    inode: &Arc<dyn Inode>,
+    name: &str,
+    value: &[u8],
+    flags: XattrSetFlags,
+) -> Result<()> {
+    inode.set_xattr(
+        XattrName::try_from_full_name(name).unwrap(),
+        &mut VmReader::from(value).to_fallible(),
+        flags,
+    )
+}
+
+/// Reads the NUL-separated names of the xattrs of the regular inode.
+fn read_xattr_list(inode: &Arc<dyn Inode>, namespace: XattrNamespace) -> Result<Vec<u8>> {
+    let list_len = inode.list_xattr(
+        namespace,
+        &mut VmWriter::from([].as_mut_slice()).to_fallible(),
+    )?;
+    if list_len == 0 {
+        return Ok(Vec::new());
+    }
+    let mut list = vec![0u8; list_len];
+    inode.list_xattr(
+        namespace,
+        &mut VmWriter::from(list.as_mut_slice()).to_fallible(),
+    )?;
+    Ok(list)
+}
+
+/// Reads the entries of the regular directory, except `.` and `..`.
+fn read_dir_entries(dir: &Arc<dyn Inode>) -> Result<Vec<(String, u64, InodeType)>> {
+    let mut visitor = LayerDirVisitor(Vec::new());
+    dir.readdir_at(0, &mut visitor)?;
+    Ok(visitor.0)
+}
+
+/// Removes all the children of the regular directory recursively.
+fn remove_all_children(dir: &Arc<dyn Inode>) -> Result<()> {
+    for (name, _, type_) in read_dir_entries(dir)? {
+        if type_ == InodeType::Dir {
+            remove_all_children(&dir.lookup(&name)?)?;
+            dir.rmdir(&name)?;
+        } else {
+            dir.unlink(&name)?;
+        }
+    }
+    Ok(())
 }
 
 #[inherit_methods(from = "self")]
@@ -913,6 +1275,9 @@ impl Inode for OverlayInode {
     fn sync_all(&self) -> Result<()>;
     fn sync_data(&self) -> Result<()>;
     fn fallocate(&self, mode: FallocMode, offset: usize, len: usize) -> Result<()>;
+    fn next_data_extent(&self, offset: usize) -> Result<Option<Range<usize>>>;
+    fn flags(&self) -> InodeFlags;
+    fn set_flags(&self, flags: InodeFlags) -> Result<()>;
     fn fs(&self) -> Arc<dyn FileSystem>;
     fn set_xattr(
         &self,
@@ -928,113 +1293,40 @@ impl Inode for OverlayInode {
 /// The index of the layer of an `OverlayFS`.
 type LayerIdx = u8; // Currently only support 256 layers.
 
-/// A visitor used by `OverlayFS` that merges the objects
-/// from the upper layer and the lower layer.
-struct OverlayDirVisitor {
-    dir_map: BTreeMap<usize, (String, u64, InodeType)>,
-    dir_set: HashSet<String>,
-    whiteout_set: HashSet<String>,
-    visited_files: usize,
-    cur_layer: LayerIdx,
-    whiteout_only_mode: bool,
+/// An entry in the unified view of an overlay directory.
+struct OverlayDirEntry {
+    name: String,
+    ino: u64,
+    type_: InodeType,
 }
 
-impl DirentVisitor for OverlayDirVisitor {
-    fn visit(&mut self, name: &str, fs_ino: u64, type_: InodeType, fs_offset: usize) -> Result<()> {
-        if self.whiteout_only_mode && name.starts_with(WHITEOUT_PREFIX) {
-            self.dir_set
-                .insert(name[WHITEOUT_PREFIX_SIZE..].to_string());
-            return Ok(());
-        }
-
-        let unique_offset = UniqueNoGenerator::gen_unique_offset(self.cur_layer, fs_offset)?;
-        let unique_ino = UniqueNoGenerator::gen_unique_ino(self.cur_layer, fs_ino)?;
-
-        if self.dir_set.contains(name) || self.whiteout_set.contains(name) {
-            return Ok(());
-        }
-        if name.starts_with(WHITEOUT_PREFIX) {
-            self.whiteout_set
-                .insert(name[WHITEOUT_PREFIX_SIZE..].to_string());
-            return Ok(());
-        }
+impl OverlayDirEntry {
+    fn new(name: String, ino: u64, type_: InodeType) -> Self {
+        Self { name, ino, type_ }
+    }
+}
 
-        let name = name.to_string();
-        self.dir_set.insert(name.clone());
+/// A visitor that collects the entries of a regular directory in a layer.
+struct LayerDirVisitor(Vec<(String, u64, InodeType)>);
 
+impl DirentVisitor for LayerDirVisitor {
+    fn visit(&mut self, name: &str, ino: u64, type_: InodeType, _offset: usize) -> Result<()> {
         if name != "." && name != ".." {
-            self.visited_files += 1;
+            self.0.push((name.to_string(), ino, type_));
         }
-
-        debug_assert!(!self.dir_map.contains_key(&unique_offset));
-        let _ = self
-            .dir_map
-            .insert(unique_offset, (name, unique_ino, type_));
         Ok(())
     }
 }
 
-impl OverlayDirVisitor {
-    pub fn new() -> Self {
-        Self {
-            dir_map: BTreeMap::new(),
-            dir_set: HashSet::new(),
-            whiteout_set: HashSet::new(),
-            visited_files: 0,
-            cur_layer: 0,
-            whiteout_only_mode: false,
-        }
-    }
-
-    /// Returns the merged view of the directory.
-    pub fn as_merged_view(&self) -> impl Iterator<Item = (&usize, &(String, u64, InodeType))> + '_ {
-        self.dir_map.iter()
-    }
-
-    pub fn visited_files(&self) -> usize {
-        self.visited_files
-    }
-
-    pub fn contains_whiteout(&self) -> bool {
-        !self.whiteout_set.is_empty()
-    }
-
-    pub fn cur_offset(&self) -> usize {
-        self.dir_map
-            .last_key_value()
-            .map(|(off, _)| *off)
-            .unwrap_or(0)
-    }
-
-    fn set_cur_layer(&mut self, layer: LayerIdx) {
-        self.cur_layer = layer;
-    }
-
-    fn set_whiteout_only_mode(&mut self) {
-        self.whiteout_only_mode = true;
-    }
-
-    fn unset_whiteout_only_mode(&mut self) {
-        self.whiteout_only_mode = false;
-    }
-}
-
 struct UniqueNoGenerator;
 
-// Unique offset and ino layout: `| LayerIdx (8 bits) | Real fs offset or ino (56 bits) |`
+// Unique ino layout: `| LayerIdx (8 bits) | Real fs ino (56 bits) |`
 impl UniqueNoGenerator {
     const NUM_HIGHER_BITS: usize = 8;
     const NUM_LOWER_BITS: usize = 56;
     const HIGHER_MASK: usize = 0xFF00_0000_0000_0000;
     const LOWER_MASK: usize = 0x00FF_FFFF_FFFF_FFFF;
 
-    pub fn gen_unique_offset(layer_idx: LayerIdx, fs_offset: usize) -> Result<usize> {
-        if fs_offset & Self::HIGHER_MASK != 0 {
-            return_errno_with_message!(Errno::EOVERFLOW, "fs offset overflow");
-        }
-        Ok(((layer_idx as usize) << Self::NUM_LOWER_BITS) | fs_offset)
-    }
-
     // XXX: Linux uses embedded fsid for unique ino. Should we follow the technique?
     pub fn gen_unique_ino(layer_idx: LayerIdx, fs_ino: u64) -> Result<u64> {
         if (fs_ino as usize) & Self::HIGHER_MASK != 0 {
@@ -1042,12 +1334,6 @@ impl UniqueNoGenerator {
         }
         Ok(((layer_idx as u64) << Self::NUM_LOWER_BITS) | fs_ino)
     }
-
-    pub fn parse_unique_offset(offset: usize) -> (LayerIdx, usize) {
-        let layer = (offset >> Self::NUM_LOWER_BITS) as LayerIdx;
-        let offset = offset & Self::LOWER_MASK;
-        (layer, offset)
-    }
 }
 
 /// Holds various mode settings and feature toggles.
@@ -1078,14 +1364,20 @@ mod tests {
     use super::*;
     use crate::fs::{path::MountNode, ramfs::RamFS};
 
-    fn create_overlay_fs() -> Arc<dyn FileSystem> {
+    /// Creates the upper layer and the work directory in the same file system.
+    fn create_upper_and_work() -> (Dentry, Dentry) {
+        let mode = InodeMode::all();
+        let root = Dentry::new_fs_root(MountNode::new_root(RamFS::new()));
+        let upper = root.new_fs_child("upper", InodeType::Dir, mode).unwrap();
+        let work = root.new_fs_child("work", InodeType::Dir, mode).unwrap();
+        (upper, work)
+    }
+
+    fn create_layers() -> (Dentry, Vec<Dentry>, Dentry) {
         crate::time::clocks::init_for_ktest();
 
         let mode = InodeMode::all();
-        let upper = {
-            let root_mount = MountNode::new_root(RamFS::new());
-            Dentry::new_fs_root(root_mount)
-        };
+        let (upper, work) = create_upper_and_work();
         let lower = {
             let r1 = MountNode::new_root(RamFS::new());
             let r2 = MountNode::new_root(RamFS::new());
@@ -1115,27 +1407,50 @@ mod tests {
 
             vec![l1, l2]
         };
-        let work = upper.clone();
+
+        (upper, lower, work)
+    }
+
+    fn create_overlay_fs() -> Arc<dyn FileSystem> {
+        let (upper, lower, work) = create_layers();
 
         let fs = OverlayFS::new(upper, lower, work).unwrap();
         assert_eq!(fs.sb().magic, OVERLAY_FS_MAGIC);
         fs
     }
 
+    fn create_whiteout(dir: &Dentry, name: &str) {
+        dir.mknod(
+            name,
+            InodeMode::empty(),
+            MknodType::CharDeviceNode(Arc::new(Whiteout)),
+        )
+        .unwrap();
+    }
+
+    fn list(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names = Vec::<String>::new();
+        dir.readdir_at(0, &mut names).unwrap();
+        names
+    }
+
+    fn listed_ino(dir: &Arc<dyn Inode>, name: &str) -> u64 {
+        let dir = dir.downcast_ref::<OverlayInode>().unwrap();
+        let entries = dir.merged_entries().unwrap();
+        entries.iter().find(|entry| entry.name == name).unwrap().ino
+    }
+
     #[ktest]
     fn obscured_multi_layers() {
         crate::time::clocks::init_for_ktest();
 
         let mode = InodeMode::all();
-        let upper = {
-            let root = Dentry::new_fs_root(MountNode::new_root(RamFS::new()));
-            root.new_fs_child("f1", InodeType::File, mode).unwrap();
-            root.new_fs_child(".wh.f2", InodeType::File, mode).unwrap();
-            root.new_fs_child("d1", InodeType::Dir, mode).unwrap();
-            root.new_fs_child("d2", InodeType::Dir, mode).unwrap();
-            root.new_fs_child(".wh.d3", InodeType::Dir, mode).unwrap();
-            root
-        };
+        let (upper, work) = create_upper_and_work();
+        upper.new_fs_child("f1", InodeType::File, mode).unwrap();
+        create_whiteout(&upper, "f2");
+        upper.new_fs_child("d1", InodeType::Dir, mode).unwrap();
+        upper.new_fs_child("d2", InodeType::Dir, mode).unwrap();
+        create_whiteout(&upper, "d3");
         let lower = {
             let l1 = {
                 let r1 = Dentry::new_fs_root(MountNode::new_root(RamFS::new()));
@@ -1150,6 +1465,7 @@ mod tests {
                 .unwrap();
                 r1.new_fs_child("d2", InodeType::File, mode).unwrap();
                 r1.new_fs_child("d3", InodeType::Dir, mode).unwrap();
+                create_whiteout(&r1, "d5");
                 r1
             };
             let l2 = {
@@ -1158,11 +1474,11 @@ mod tests {
                 r2.new_fs_child("d1", InodeType::Dir, mode).unwrap();
                 r2.new_fs_child("d2", InodeType::Dir, mode).unwrap();
                 r2.new_fs_child("d4", InodeType::Dir, mode).unwrap();
+                r2.new_fs_child("d5", InodeType::Dir, mode).unwrap();
                 r2
             };
             vec![l1, l2]
         };
-        let work = upper.clone();
 
         let fs = OverlayFS::new(upper, lower, work).unwrap();
         let root = fs.root_inode();
@@ -1192,6 +1508,12 @@ mod tests {
         assert_eq!(d4.type_(), InodeType::Dir);
         let d4_inode = d4.downcast_ref::<OverlayInode>().unwrap();
         assert!(!d4_inode.has_valid_upper() && d4_inode.num_lowers() == 1);
+
+        let e = root.lookup("d5").expect_err("");
+        assert_eq!(e.error(), Errno::ENOENT);
+
+        // The whiteouts are hidden, and so are the inodes obscured by them.
+        assert_eq!(list(&root), [".", "..", "f1", "d1", "d2", "d4"]);
     }
 
     #[ktest]
@@ -1227,8 +1549,16 @@ mod tests {
         };
         assert_eq!(e.error(), Errno::EEXIST);
         root.unlink("f1").unwrap();
+        assert_eq!(list(&root), [".", "..", "d1", "f2"]);
+
+        // The whiteout is a character device with the device number 0/0.
+        let fs = fs.downcast_ref::<OverlayFS>().unwrap();
+        let whiteout = fs.upper.dentry.inode().lookup("f1").unwrap();
+        assert!(is_whiteout(&whiteout));
 
         root.create("f1", InodeType::File, mode).unwrap();
+        let file = fs.upper.dentry.inode().lookup("f1").unwrap();
+        assert_eq!(file.type_(), InodeType::File);
     }
 
     #[ktest]
@@ -1244,10 +1574,12 @@ mod tests {
 
         let d1 = root.lookup("d1").unwrap();
         d1.unlink("f11").unwrap();
+        assert_eq!(root.rmdir("d1").unwrap_err().error(), Errno::ENOTEMPTY);
         d1.unlink("f12").unwrap();
 
         root.rmdir("d1").unwrap();
         let d1 = root.create("d1", InodeType::Dir, mode).unwrap();
+        assert_eq!(list(&d1), [".", ".."]);
         d1.create("f11", InodeType::File, mode).unwrap();
     }
 
@@ -1272,6 +1604,96 @@ mod tests {
         )
         .unwrap();
         assert_eq!(xattr_value.as_slice(), "f2_xattr_value".as_bytes());
+
+        // The private xattrs are hidden.
+        let list = read_xattr_list(&f2, XattrNamespace::Trusted).unwrap();
+        assert_eq!(list.as_slice(), b"trusted.f2_xattr_name\0");
+
+        // Nothing is left in the work directory.
+        let fs = fs.downcast_ref::<OverlayFS>().unwrap();
+        assert!(read_dir_entries(&fs.work.dir).unwrap().is_empty());
+    }
+
+    #[ktest]
+    fn stable_ino() {
+        let (upper, lower, work) = create_layers();
+        let fs = OverlayFS::new(upper.clone(), lower.clone(), work.clone()).unwrap();
+        let root = fs.root_inode();
+
+        let f2 = root.lookup("f2").unwrap();
+        let ino = f2.ino();
+        assert_eq!(listed_ino(&root, "f2"), ino);
+        assert_ne!(listed_ino(&root, "f1"), ino);
+
+        // The inode number is kept after the copy-up, even after remounting.
+        f2.write_bytes_at(0, &[1u8]).unwrap();
+        assert_eq!(f2.ino(), ino);
+        assert_eq!(f2.metadata().ino, ino);
+        assert_eq!(listed_ino(&root, "f2"), ino);
+        assert!(Arc::ptr_eq(&root.lookup("f2").unwrap(), &f2));
+
+        let fs = OverlayFS::new(upper, lower, work).unwrap();
+        let root = fs.root_inode();
+        assert_eq!(root.lookup("f2").unwrap().ino(), ino);
+        assert_eq!(listed_ino(&root, "f2"), ino);
+    }
+
+    #[ktest]
+    fn rename() {
+        let fs = create_overlay_fs();
+        let root = fs.root_inode();
+        let mode = InodeMode::all();
+
+        // The lower file is copied up, then renamed.
+        let f2 = root.lookup("f2").unwrap();
+        root.rename("f2", &root, "f3").unwrap();
+        assert_eq!(root.lookup("f2").unwrap_err().error(), Errno::ENOENT);
+        assert!(Arc::ptr_eq(&root.lookup("f3").unwrap(), &f2));
+        assert_eq!(list(&root), [".", "..", "f3", "f1", "d1"]);
+
+        // The copied-up file is still usable.
+        f2.write_bytes_at(0, &[1u8]).unwrap();
+        let mut data = [0u8; 4];
+        root.lookup("f3")
+            .unwrap()
+            .read_bytes_at(0, data.as_mut_slice())
+            .unwrap();
+        assert_eq!(data, [1u8, 8, 8, 8]);
+
+        // The lower file is replaced.
+        root.rename("f3", &root, "f1").unwrap();
+        assert_eq!(root.lookup("f1").unwrap().size(), 4);
+        assert_eq!(list(&root), [".", "..", "f1", "d1"]);
+
+        // The merged directory cannot be renamed.
+        assert_eq!(
+            root.rename("d1", &root, "d2").unwrap_err().error(),
+            Errno::EXDEV
+        );
+
+        // The upper directory becomes opaque when moved into a merged directory.
+        let d2 = root.create("d2", InodeType::Dir, mode).unwrap();
+        d2.create("f21", InodeType::File, mode).unwrap();
+        let d1 = root.lookup("d1").unwrap();
+        root.rename("d2", &d1, "f11").unwrap_err();
+        d1.unlink("f11").unwrap();
+        root.rename("d2", &d1, "f11").unwrap();
+        let f11 = d1.lookup("f11").unwrap();
+        assert_eq!(f11.type_(), InodeType::Dir);
+        assert_eq!(list(&f11), [".", "..", "f21"]);
+        assert_eq!(list(&d1), [".", "..", "f11", "f12"]);
+    }
+
+    #[ktest]
+    fn invalid_layers() {
+        let (upper, lower, work) = create_layers();
+
+        let e = OverlayFS::new(upper.clone(), Vec::new(), work).unwrap_err();
+        assert_eq!(e.error(), Errno::EINVAL);
+        let e = OverlayFS::new(upper.clone(), lower.clone(), upper.clone()).unwrap_err();
+        assert_eq!(e.error(), Errno::EINVAL);
+        let e = OverlayFS::new(upper, lower.clone(), lower[0].clone()).unwrap_err();
+        assert_eq!(e.error(), Errno::EINVAL);
     }
 
     #[ktest]
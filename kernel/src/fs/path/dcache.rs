@@ -0,0 +1,402 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The dentry cache (dcache).
+//!
+//! Each directory `Dentry_` caches its children in a `DentryChildren`,
+//! including the _negative_ ones that record failed lookups.
+//! The `DentryChildren` is protected by a `RwMutex`,
+//! which serializes the modifications to the directory.
+//!
+//! In addition, all the cached children are indexed by a global hash table,
+//! whose key is the parent and the name of a child.
+//! The buckets of the table are protected by RCU,
+//! so that path resolution finds the cached children without taking any lock
+//! (the fast path, see [`lookup`]).
+//! Only cache misses go through the per-directory lock and the file system (the slow path).
+//!
+//! The cache is shrunk in an approximate LRU order
+//! when it grows beyond [`MAX_CACHED_DENTRIES`] or the system runs low on memory.
+//! A child is reclaimed only if it is unused,
+//! i.e., it is negative or it is held by nothing but its parent.
+//! A child that has been looked up since the last scan gets a second chance.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ostd::sync::RcuOption;
+
+use super::dentry::{Dentry_, Reclaim};
+use crate::prelude::*;
+
+/// The number of the buckets in the hash table.
+const NR_BUCKETS: usize = 1 << 12;
+
+/// The maximum number of the cached children (including the negative ones).
+const MAX_CACHED_DENTRIES: usize = 1 << 16;
+
+/// The number of the children that are scanned at most in a round of shrinking.
+const SHRINK_BATCH: usize = 128;
+
+/// The system is considered to be low on memory
+/// if less than `1 / LOW_MEMORY_RATIO` of the memory is free.
+const LOW_MEMORY_RATIO: usize = 16;
+
+static HASH_TABLE: [Bucket; NR_BUCKETS] = [const { Bucket::new() }; NR_BUCKETS];
+
+/// The cached children, from the least recently added (or rescued) to the most.
+///
+/// An item may be stale if the child has been removed from the cache,
+/// which is skipped when the item is scanned.
+static LRU_LIST: SpinLock<VecDeque<LruItem>> = SpinLock::new(VecDeque::new());
+
+/// The number of the cached children.
+static NR_CACHED: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of the insertions since the last check of the free memory.
+static NR_INSERTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The result of a lookup in the dcache.
+pub(super) enum CacheLookup {
+    /// A cached child is found.
+    Positive(Arc<Dentry_>),
+    /// A negative child is found, i.e., the file does not exist.
+    Negative,
+    /// Nothing is cached for the name.
+    Miss,
+}
+
+/// Looks up a child of `parent` in the dcache without taking any lock.
+pub(super) fn lookup(parent: &Dentry_, name: &str) -> CacheLookup {
+    let parent_key = parent as *const Dentry_ as usize;
+    let bucket = &HASH_TABLE[bucket_index(parent_key, name)];
+
+    let entries = bucket.entries.read();
+    let Some(entries) = entries.get() else {
+        return CacheLookup::Miss;
+    };
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.parent == parent_key && entry.name == name)
+    else {
+        return CacheLookup::Miss;
+    };
+
+    match entry.child.as_ref() {
+        None => CacheLookup::Negative,
+        // The child may be being dropped, in which case the slow path will find out.
+        Some(child) => match child.upgrade() {
+            Some(child) => {
+                child.mark_referenced();
+                CacheLookup::Positive(child)
+            }
+            None => CacheLookup::Miss,
+        },
+    }
+}
+
+/// Adds a child (or a negative one if `child` is `None`) of `parent` to the hash table,
+/// replacing the existing one of the same name.
+///
+/// The caller must hold the lock of the children of `parent`.
+pub(super) fn hash(parent: &Weak<Dentry_>, name: &str, child: Option<&Arc<Dentry_>>) {
+    let parent_key = parent.as_ptr() as usize;
+    let new_entry = HashEntry {
+        parent: parent_key,
+        name: String::from(name),
+        child: child.map(Arc::downgrade),
+    };
+
+    let mut is_new = false;
+    HASH_TABLE[bucket_index(parent_key, name)].update(|entries| {
+        match entries
+            .iter_mut()
+            .find(|entry| entry.parent == parent_key && entry.name == name)
+        {
+            Some(entry) => *entry = new_entry,
+            None => {
+                entries.push(new_entry);
+                is_new = true;
+            }
+        }
+    });
+    if !is_new {
+        return;
+    }
+
+    NR_CACHED.fetch_add(1, Ordering::Relaxed);
+    NR_INSERTIONS.fetch_add(1, Ordering::Relaxed);
+    LRU_LIST.lock().push_back(LruItem {
+        parent: parent.clone(),
+        name: String::from(name),
+    });
+}
+
+/// Removes a child of `parent` from the hash table.
+///
+/// The caller must hold the lock of the children of `parent`,
+/// or own the children exclusively.
+pub(super) fn unhash(parent: &Weak<Dentry_>, name: &str) {
+    let parent_key = parent.as_ptr() as usize;
+
+    let mut is_removed = false;
+    HASH_TABLE[bucket_index(parent_key, name)].update(|entries| {
+        let old_len = entries.len();
+        entries.retain(|entry| entry.parent != parent_key || entry.name != name);
+        is_removed = entries.len() != old_len;
+    });
+    if is_removed {
+        NR_CACHED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Shrinks the dcache if it grows too large or the system runs low on memory.
+///
+/// The caller must not hold the lock of any `DentryChildren`.
+pub(super) fn shrink_if_needed() {
+    let nr_cached = NR_CACHED.load(Ordering::Relaxed);
+    if nr_cached > MAX_CACHED_DENTRIES {
+        shrink(nr_cached - MAX_CACHED_DENTRIES + SHRINK_BATCH);
+        return;
+    }
+
+    // Checking the free memory is not free, so do it once per batch of insertions.
+    if NR_INSERTIONS.load(Ordering::Relaxed) < SHRINK_BATCH {
+        return;
+    }
+    NR_INSERTIONS.store(0, Ordering::Relaxed);
+    let free_size = osdk_frame_allocator::load_total_free_size();
+    if free_size < crate::vm::mem_total() / LOW_MEMORY_RATIO {
+        shrink(SHRINK_BATCH);
+    }
+}
+
+/// Scans at most `nr_to_scan` children in the LRU order and reclaims the unused ones.
+///
+/// Returns the number of the reclaimed children.
+fn shrink(nr_to_scan: usize) -> usize {
+    let mut nr_reclaimed = 0;
+
+    for _ in 0..nr_to_scan {
+        let Some(item) = LRU_LIST.lock().pop_front() else {
+            break;
+        };
+        let Some(parent) = item.parent.upgrade() else {
+            // The children of a dropped parent have been unhashed.
+            continue;
+        };
+
+        match parent.reclaim_child(&item.name) {
+            Reclaim::Reclaimed => nr_reclaimed += 1,
+            Reclaim::Busy => LRU_LIST.lock().push_back(item),
+            Reclaim::NotCached => (),
+        }
+    }
+
+    nr_reclaimed
+}
+
+struct Bucket {
+    entries: RcuOption<Box<Vec<HashEntry>>>,
+    /// Serializes the updates to `entries`.
+    update_lock: SpinLock<()>,
+}
+
+impl Bucket {
+    const fn new() -> Self {
+        Self {
+            entries: RcuOption::new_none(),
+            update_lock: SpinLock::new(()),
+        }
+    }
+
+    /// Updates the entries by copying them, so that readers are never blocked.
+    fn update(&self, op: impl FnOnce(&mut Vec<HashEntry>)) {
+        let _guard = self.update_lock.lock();
+
+        let mut entries = self
+            .entries
+            .read()
+            .get()
+            .map(|entries| Vec::clone(&entries))
+            .unwrap_or_default();
+        op(&mut entries);
+
+        let new_entries = if entries.is_empty() {
+            None
+        } else {
+            Some(Box::new(entries))
+        };
+        self.entries.update(new_entries);
+    }
+}
+
+#[derive(Clone)]
+struct HashEntry {
+    /// The address of the parent `Dentry_`.
+    parent: usize,
+    name: String,
+    /// The child, or `None` if it is negative.
+    child: Option<Weak<Dentry_>>,
+}
+
+struct LruItem {
+    parent: Weak<Dentry_>,
+    name: String,
+}
+
+/// Returns the index of the bucket of a child using the FNV-1a hash.
+fn bucket_index(parent_key: usize, name: &str) -> usize {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = name
+        .bytes()
+        .chain((parent_key as u64).to_le_bytes())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+    (hash as usize) % NR_BUCKETS
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::fs::{
+        ramfs::RamFS,
+        utils::{FileSystem, InodeMode, InodeType},
+    };
+
+    fn new_ramfs() -> (Arc<RamFS>, Arc<Dentry_>) {
+        crate::time::clocks::init_for_ktest();
+        let fs = RamFS::new();
+        let root = Dentry_::new_root(fs.root_inode());
+        (fs, root)
+    }
+
+    fn create_file(dir: &Dentry_, name: &str) -> Arc<Dentry_> {
+        dir.create(name, InodeType::File, InodeMode::from_bits_truncate(0o644))
+            .unwrap()
+    }
+
+    fn is_positive(parent: &Dentry_, name: &str, expected: &Arc<Dentry_>) -> bool {
+        match lookup(parent, name) {
+            CacheLookup::Positive(child) => Arc::ptr_eq(&child, expected),
+            _ => false,
+        }
+    }
+
+    fn is_negative(parent: &Dentry_, name: &str) -> bool {
+        matches!(lookup(parent, name), CacheLookup::Negative)
+    }
+
+    fn is_miss(parent: &Dentry_, name: &str) -> bool {
+        matches!(lookup(parent, name), CacheLookup::Miss)
+    }
+
+    #[ktest]
+    fn negative_lookup() {
+        let (_fs, root) = new_ramfs();
+        assert!(is_miss(&root, "file"));
+
+        // A failed lookup leaves a negative child.
+        assert_eq!(
+            root.lookup_via_fs("file").unwrap_err().error(),
+            Errno::ENOENT
+        );
+        assert!(is_negative(&root, "file"));
+        assert_eq!(
+            root.lookup_via_cache("file").unwrap_err().error(),
+            Errno::ENOENT
+        );
+
+        // Creating the file replaces the negative child.
+        let file = create_file(&root, "file");
+        assert!(is_positive(&root, "file", &file));
+        assert!(Arc::ptr_eq(
+            &root.lookup_via_cache("file").unwrap().unwrap(),
+            &file
+        ));
+
+        // Unlinking the file turns the child negative again.
+        root.unlink("file").unwrap();
+        assert!(is_negative(&root, "file"));
+    }
+
+    #[ktest]
+    fn rename_invalidation() {
+        let (_fs, root) = new_ramfs();
+        let dir = root
+            .create("dir", InodeType::Dir, InodeMode::from_bits_truncate(0o755))
+            .unwrap();
+
+        let file = create_file(&root, "old");
+        assert!(root.lookup_via_fs("new").is_err());
+        assert!(is_negative(&root, "new"));
+
+        // Renaming in the same directory.
+        root.rename("old", &root, "new").unwrap();
+        assert!(is_negative(&root, "old"));
+        assert!(is_positive(&root, "new", &file));
+
+        // Renaming across directories.
+        assert!(dir.lookup_via_fs("file").is_err());
+        assert!(is_negative(&dir, "file"));
+        root.rename("new", &dir, "file").unwrap();
+        assert!(is_negative(&root, "new"));
+        assert!(is_positive(&dir, "file", &file));
+
+        // Renaming an uncached file over a cached one.
+        drop(create_file(&root, "other"));
+        assert!(matches!(root.reclaim_child("other"), Reclaim::Reclaimed));
+        root.rename("other", &dir, "file").unwrap();
+        assert!(is_miss(&root, "other"));
+        assert!(is_miss(&dir, "file"));
+        let other = dir.lookup_via_fs("file").unwrap();
+        assert!(!Arc::ptr_eq(&other, &file));
+        assert!(is_positive(&dir, "file", &other));
+    }
+
+    #[ktest]
+    fn reclaim_children() {
+        let (_fs, root) = new_ramfs();
+        let held = create_file(&root, "held");
+        drop(create_file(&root, "unused"));
+        assert!(root.lookup_via_fs("negative").is_err());
+
+        // The child is busy while it is held, and it gets a second chance
+        // after being looked up.
+        assert!(matches!(root.reclaim_child("held"), Reclaim::Busy));
+        assert!(is_positive(
+            &root,
+            "unused",
+            &root.lookup_via_fs("unused").unwrap()
+        ));
+        assert!(matches!(root.reclaim_child("unused"), Reclaim::Busy));
+        assert!(matches!(root.reclaim_child("negative"), Reclaim::Reclaimed));
+        assert!(matches!(root.reclaim_child("negative"), Reclaim::NotCached));
+        assert!(is_miss(&root, "negative"));
+
+        // Shrinking scans every child once, so the held one survives.
+        let nr_to_scan = LRU_LIST.lock().len();
+        shrink(nr_to_scan);
+        assert!(is_positive(&root, "held", &held));
+        assert!(is_miss(&root, "unused"));
+
+        // A cached child holds its parent, so it has to be reclaimed before the parent is dropped.
+        drop(held);
+        assert!(matches!(root.reclaim_child("held"), Reclaim::Busy));
+        assert!(matches!(root.reclaim_child("held"), Reclaim::Reclaimed));
+
+        // The negative children are unhashed with their parent.
+        assert!(root.lookup_via_fs("negative").is_err());
+        let root_key = Arc::as_ptr(&root) as usize;
+        drop(root);
+        let bucket = &HASH_TABLE[bucket_index(root_key, "negative")];
+        assert!(bucket
+            .entries
+            .read()
+            .get()
+            .is_none_or(|entries| entries.iter().all(|entry| entry.parent != root_key)));
+    }
+}
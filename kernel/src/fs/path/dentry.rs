@@ -12,17 +12,21 @@ use hashbrown::HashMap;
 use inherit_methods_macro::inherit_methods;
 use ostd::sync::RwMutexWriteGuard;
 
-use super::{is_dot, is_dot_or_dotdot, is_dotdot};
+use super::{
+    dcache::{self, CacheLookup},
+    is_dot, is_dot_or_dotdot, is_dotdot,
+};
 use crate::{
     fs::{
-        path::mount::MountNode,
+        path::mount::{MountNode, PerMountFlags},
         utils::{
-            FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType, Permission, XattrName,
-            XattrNamespace, XattrSetFlags, NAME_MAX,
+            FileSystem, Inode, InodeFlags, InodeMode, InodeType, Metadata, MknodType, Permission,
+            XattrName, XattrNamespace, XattrSetFlags, NAME_MAX,
         },
     },
     prelude::*,
     process::{Gid, Uid},
+    time::clocks::RealTimeCoarseClock,
 };
 
 /// A `Dentry` is used to represent a location in the mount tree.
@@ -40,6 +44,8 @@ pub struct Dentry_ {
     name_and_parent: RwLock<Option<(String, Arc<Dentry_>)>>,
     children: RwMutex<DentryChildren>,
     flags: AtomicU32,
+    /// The number of the mounts that are mounted on the `Dentry_`.
+    nr_mounts: AtomicU32,
     this: Weak<Dentry_>,
 }
 
@@ -60,8 +66,9 @@ impl Dentry_ {
                 DentryOptions::Leaf(name_and_parent) => RwLock::new(Some(name_and_parent)),
                 _ => RwLock::new(None),
             },
-            children: RwMutex::new(DentryChildren::new()),
+            children: RwMutex::new(DentryChildren::new(weak_self.clone())),
             flags: AtomicU32::new(DentryFlags::empty().bits()),
+            nr_mounts: AtomicU32::new(0),
             this: weak_self.clone(),
         })
     }
@@ -129,20 +136,67 @@ impl Dentry_ {
     }
 
     pub fn is_mountpoint(&self) -> bool {
-        self.flags().contains(DentryFlags::MOUNTED)
+        self.nr_mounts.load(Ordering::Acquire) > 0
     }
 
+    /// Records that one more mount is mounted on the `Dentry_`.
+    ///
+    /// A `Dentry_` can be the mountpoint of multiple mounts at the same time,
+    /// e.g., after it is bind mounted or a mount is propagated to it.
     pub fn set_mountpoint_dentry(&self) {
-        self.flags
-            .fetch_or(DentryFlags::MOUNTED.bits(), Ordering::Release);
+        self.nr_mounts.fetch_add(1, Ordering::Release);
     }
 
+    /// Records that one of the mounts on the `Dentry_` is unmounted.
     pub fn clear_mountpoint(&self) {
-        self.flags
-            .fetch_and(!(DentryFlags::MOUNTED.bits()), Ordering::Release);
+        let _ = self
+            .nr_mounts
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |nr| nr.checked_sub(1));
+    }
+
+    /// Marks that the `Dentry_` has been looked up from the dcache.
+    pub(super) fn mark_referenced(&self) {
+        if !self.flags().contains(DentryFlags::REFERENCED) {
+            self.flags
+                .fetch_or(DentryFlags::REFERENCED.bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Clears the mark of being looked up, returning whether it was marked.
+    fn test_and_clear_referenced(&self) -> bool {
+        let old_flags = self
+            .flags
+            .fetch_and(!(DentryFlags::REFERENCED.bits()), Ordering::Relaxed);
+        old_flags & DentryFlags::REFERENCED.bits() != 0
+    }
+
+    /// Tries to reclaim a cached child with the given name.
+    ///
+    /// A valid child can be reclaimed only if it is held by nothing but its parent
+    /// and it has not been looked up since the last try.
+    /// A negative child can always be reclaimed.
+    pub(super) fn reclaim_child(&self, name: &str) -> Reclaim {
+        let mut children = self.children.write();
+
+        let is_busy = match children.dentries.get(name) {
+            None => return Reclaim::NotCached,
+            Some(None) => false,
+            // Note that a child with cached children is held by them.
+            Some(Some(child)) => {
+                child.test_and_clear_referenced()
+                    || Arc::strong_count(child) > 1
+                    || child.is_mountpoint()
+            }
+        };
+        if is_busy {
+            return Reclaim::Busy;
+        }
+
+        children.remove(name);
+        Reclaim::Reclaimed
     }
 
-    /// Currently, the root `Dentry_` of a fs is the root of a mount.
+    /// Checks whether the `Dentry_` is the root of its file system.
     pub fn is_root_of_mount(&self) -> bool {
         self.name_and_parent.read().as_ref().is_none()
     }
@@ -169,16 +223,48 @@ impl Dentry_ {
         Ok(new_child)
     }
 
-    /// Lookups a target `Dentry_` from the cache in children.
+    /// Creates a `Dentry_` by creating a new symbolic link that points to `target`.
+    pub fn symlink(&self, name: &str, target: &str) -> Result<Arc<Self>> {
+        if self.type_() != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
+
+        let children = self.children.upread();
+        if children.contains_valid(name) {
+            return_errno!(Errno::EEXIST);
+        }
+
+        let new_inode = self.inode.symlink(name, target)?;
+        let name = String::from(name);
+        let new_child = Dentry_::new(new_inode, DentryOptions::Leaf((name.clone(), self.this())));
+
+        if new_child.is_dentry_cacheable() {
+            children.upgrade().insert(name, new_child.clone());
+        }
+
+        Ok(new_child)
+    }
+
+    /// Lookups a target `Dentry_` from the dcache without taking any lock.
     pub fn lookup_via_cache(&self, name: &str) -> Result<Option<Arc<Dentry_>>> {
-        let children = self.children.read();
-        children.find(name)
+        match dcache::lookup(self, name) {
+            CacheLookup::Positive(target) => Ok(Some(target)),
+            CacheLookup::Negative => {
+                return_errno_with_message!(Errno::ENOENT, "found a negative dentry")
+            }
+            CacheLookup::Miss => Ok(None),
+        }
     }
 
     /// Lookups a target `Dentry_` from the file system.
     pub fn lookup_via_fs(&self, name: &str) -> Result<Arc<Dentry_>> {
         let children = self.children.upread();
 
+        // Another thread may have looked up the target while we were waiting for the lock.
+        if let Some(target) = children.find(name)? {
+            return Ok(target);
+        }
+
         let inode = match self.inode.lookup(name) {
             Ok(inode) => inode,
             Err(e) => {
@@ -307,8 +393,10 @@ impl Dentry_ {
                         children.insert(String::from(new_name), dentry.clone());
                     }
                 }
+                // The renamed file now exists at `new_name`, so the cached child there is
+                // stale, even as a negative one.
                 None => {
-                    children.delete(new_name);
+                    children.remove(new_name);
                 }
             }
         } else {
@@ -328,7 +416,7 @@ impl Dentry_ {
                     }
                 }
                 None => {
-                    new_dir_children.delete(new_name);
+                    new_dir_children.remove(new_name);
                 }
             }
         }
@@ -407,10 +495,20 @@ impl DentryKey {
 
 bitflags! {
     struct DentryFlags: u32 {
-        const MOUNTED = 1 << 0;
+        /// Whether the `Dentry_` has been looked up since the dcache was last shrunk.
+        const REFERENCED = 1 << 0;
     }
 }
 
+/// The result of trying to reclaim a cached child.
+pub(super) enum Reclaim {
+    Reclaimed,
+    /// The child is in use or has been used recently.
+    Busy,
+    /// The child is not in the cache.
+    NotCached,
+}
+
 enum DentryOptions {
     Root,
     Leaf((String, Arc<Dentry_>)),
@@ -420,17 +518,22 @@ enum DentryOptions {
 ///
 /// A _negative_ dentry reflects a failed filename lookup, saving potential
 /// repeated and costly lookups in the future.
-// TODO: Address the issue of negative dentry bloating. See the reference
-// https://lwn.net/Articles/894098/ for more details.
+///
+/// All the entries are also indexed by the dcache for lock-free lookups,
+/// which reclaims the unused entries to avoid negative dentry bloating.
+/// See [`dcache`] for details.
 struct DentryChildren {
     dentries: HashMap<String, Option<Arc<Dentry_>>>,
+    /// The `Dentry_` that owns the children.
+    parent: Weak<Dentry_>,
 }
 
 impl DentryChildren {
     /// Creates an empty dentry cache.
-    pub fn new() -> Self {
+    pub fn new(parent: Weak<Dentry_>) -> Self {
         Self {
             dentries: HashMap::new(),
+            parent,
         }
     }
 
@@ -458,17 +561,29 @@ impl DentryChildren {
         // Assume the caller has checked that the dentry is cacheable
         // and will be newly created if looked up from the parent.
         debug_assert!(dentry.is_dentry_cacheable());
+        dcache::hash(&self.parent, &name, Some(&dentry));
         let _ = self.dentries.insert(name, Some(dentry));
     }
 
     /// Inserts a negative dentry.
     pub fn insert_negative(&mut self, name: String) {
+        dcache::hash(&self.parent, &name, None);
         let _ = self.dentries.insert(name, None);
     }
 
     /// Deletes a dentry by name, turning it into a negative entry if exists.
     pub fn delete(&mut self, name: &str) -> Option<Arc<Dentry_>> {
-        self.dentries.get_mut(name).and_then(Option::take)
+        let dentry = self.dentries.get_mut(name).and_then(Option::take);
+        if dentry.is_some() {
+            dcache::hash(&self.parent, name, None);
+        }
+        dentry
+    }
+
+    /// Removes an entry by name, no matter whether it is valid or negative.
+    pub fn remove(&mut self, name: &str) -> Option<Arc<Dentry_>> {
+        dcache::unhash(&self.parent, name);
+        self.dentries.remove(name).flatten()
     }
 
     /// Checks whether the dentry is a mount point. Returns an error if it is.
@@ -496,6 +611,14 @@ impl DentryChildren {
     }
 }
 
+impl Drop for DentryChildren {
+    fn drop(&mut self) {
+        for name in self.dentries.keys() {
+            dcache::unhash(&self.parent, name);
+        }
+    }
+}
+
 fn write_lock_children_on_two_dentries<'a>(
     this: &'a Dentry_,
     other: &'a Dentry_,
@@ -525,6 +648,8 @@ impl Dentry {
 
     /// Creates a new `Dentry` to represent the child directory of a file system.
     pub fn new_fs_child(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Self> {
+        self.check_writable_mount()?;
+        self.check_creatable()?;
         if self
             .inode()
             .check_permission(Permission::MAY_WRITE)
@@ -536,12 +661,38 @@ impl Dentry {
         Ok(Self::new(self.mount_node.clone(), new_child_dentry))
     }
 
+    /// Creates a new `Dentry` to represent a symbolic link that points to `target`.
+    pub fn new_fs_symlink(&self, name: &str, target: &str) -> Result<Self> {
+        self.check_writable_mount()?;
+        self.check_creatable()?;
+        if self
+            .inode()
+            .check_permission(Permission::MAY_WRITE)
+            .is_err()
+        {
+            return_errno!(Errno::EACCES);
+        }
+        let new_child_dentry = self.inner.symlink(name, target)?;
+        Ok(Self::new(self.mount_node.clone(), new_child_dentry))
+    }
+
     fn new(mount_node: Arc<MountNode>, inner: Arc<Dentry_>) -> Self {
         Self { mount_node, inner }
     }
 
     /// Lookups the target `Dentry` given the `name`.
     pub fn lookup(&self, name: &str) -> Result<Self> {
+        self.lookup_with(name, false)
+    }
+
+    /// Lookups the target `Dentry` given the `name` in the dcache only.
+    ///
+    /// Returns [`Errno::EAGAIN`] if the lookup cannot be done without the file system.
+    pub fn lookup_cached(&self, name: &str) -> Result<Self> {
+        self.lookup_with(name, true)
+    }
+
+    fn lookup_with(&self, name: &str, is_cached_only: bool) -> Result<Self> {
         if self.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
@@ -560,9 +711,14 @@ impl Dentry {
             let target_inner_opt = self.inner.lookup_via_cache(name)?;
             match target_inner_opt {
                 Some(target_inner) => Self::new(self.mount_node.clone(), target_inner),
+                None if is_cached_only => {
+                    return_errno_with_message!(Errno::EAGAIN, "the dentry is not cached");
+                }
                 None => {
-                    let target_inner = self.inner.lookup_via_fs(name)?;
-                    Self::new(self.mount_node.clone(), target_inner)
+                    // The slow path may have added new entries to the dcache.
+                    let target_inner = self.inner.lookup_via_fs(name);
+                    dcache::shrink_if_needed();
+                    Self::new(self.mount_node.clone(), target_inner?)
                 }
             }
         };
@@ -598,7 +754,7 @@ impl Dentry {
     /// If it is the root of a mount, it will go up to the mountpoint
     /// to get the name of the mountpoint recursively.
     fn effective_name(&self) -> String {
-        if !self.inner.is_root_of_mount() {
+        if !self.is_root_of_mount() {
             return self.inner.name();
         }
 
@@ -620,8 +776,8 @@ impl Dentry {
     ///
     /// If it is the root of a mount, it will go up to the mountpoint
     /// to get the parent of the mountpoint recursively.
-    fn effective_parent(&self) -> Option<Self> {
-        if !self.inner.is_root_of_mount() {
+    pub(in crate::fs) fn effective_parent(&self) -> Option<Self> {
+        if !self.is_root_of_mount() {
             return Some(Self::new(
                 self.mount_node.clone(),
                 self.inner.parent().unwrap(),
@@ -656,13 +812,6 @@ impl Dentry {
         }
     }
 
-    /// Makes current `Dentry` to be a mountpoint,
-    /// sets it as the mountpoint of the child mount.
-    pub(super) fn set_mountpoint(&self, child_mount: Arc<MountNode>) {
-        child_mount.set_mountpoint_dentry(&self.inner);
-        self.inner.set_mountpoint_dentry();
-    }
-
     /// Mounts the fs on current `Dentry` as a mountpoint.
     ///
     /// If the given mountpoint has already been mounted,
@@ -671,6 +820,18 @@ impl Dentry {
     ///
     /// Returns the mounted child mount.
     pub fn mount(&self, fs: Arc<dyn FileSystem>) -> Result<Arc<MountNode>> {
+        self.mount_with_flags(fs, PerMountFlags::empty())
+    }
+
+    /// Mounts the fs on current `Dentry` as a mountpoint with the per-mount flags.
+    ///
+    /// The flags are set before the mount is propagated,
+    /// so that the propagated copies have the same flags.
+    pub fn mount_with_flags(
+        &self,
+        fs: Arc<dyn FileSystem>,
+        flags: PerMountFlags,
+    ) -> Result<Arc<MountNode>> {
         if self.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
@@ -678,16 +839,14 @@ impl Dentry {
             return_errno_with_message!(Errno::EINVAL, "can not mount on root");
         }
 
-        let child_mount = self.mount_node.mount(fs, &self.this())?;
-        self.set_mountpoint(child_mount.clone());
-        Ok(child_mount)
+        self.mount_node.mount(fs, flags, &self.this())
     }
 
     /// Unmounts and returns the mounted child mount.
     ///
     /// Note that the root mount cannot be unmounted.
     pub fn unmount(&self) -> Result<Arc<MountNode>> {
-        if !self.inner.is_root_of_mount() {
+        if !self.is_root_of_mount() {
             return_errno_with_message!(Errno::EINVAL, "not mounted");
         }
 
@@ -698,13 +857,13 @@ impl Dentry {
         let mountpoint_mount_node = self.mount_node.parent().unwrap().upgrade().unwrap();
         let mountpoint = Self::new(mountpoint_mount_node.clone(), mountpoint_dentry.clone());
 
-        let child_mount = mountpoint_mount_node.unmount(&mountpoint)?;
-        mountpoint_dentry.clear_mountpoint();
-        Ok(child_mount)
+        mountpoint_mount_node.unmount(&mountpoint)
     }
 
     /// Creates a `Dentry` by making an inode of the `type_` with the `mode`.
     pub fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Self> {
+        self.check_writable_mount()?;
+        self.check_creatable()?;
         let inner = self.inner.mknod(name, mode, type_)?;
         Ok(Self::new(self.mount_node.clone(), inner))
     }
@@ -714,16 +873,29 @@ impl Dentry {
         if !Arc::ptr_eq(&old.mount_node, &self.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_writable_mount()?;
+        self.check_creatable()?;
+        if old
+            .inode()
+            .flags()
+            .intersects(InodeFlags::IMMUTABLE | InodeFlags::APPEND)
+        {
+            return_errno_with_message!(Errno::EPERM, "the file is immutable or append-only");
+        }
         self.inner.link(&old.inner, name)
     }
 
     /// Deletes a `Dentry`.
     pub fn unlink(&self, name: &str) -> Result<()> {
+        self.check_writable_mount()?;
+        self.check_deletable(name)?;
         self.inner.unlink(name)
     }
 
     /// Deletes a directory `Dentry`.
     pub fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_writable_mount()?;
+        self.check_deletable(name)?;
         self.inner.rmdir(name)
     }
 
@@ -732,30 +904,116 @@ impl Dentry {
         if !Arc::ptr_eq(&self.mount_node, &new_dir.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_writable_mount()?;
+        self.check_deletable(old_name)?;
+        new_dir.check_creatable()?;
+        new_dir.check_deletable(new_name)?;
         self.inner.rename(old_name, &new_dir.inner, new_name)
     }
 
     /// Binds mount the `Dentry` to the destination `Dentry`.
     ///
     /// If `recursive` is true, it will bind mount the whole mount tree
-    /// to the destination `Dentry`. Otherwise, it will only bind mount
-    /// the root mount node.
+    /// to the destination `Dentry`, except for the unbindable mounts.
+    /// Otherwise, it will only bind mount the root mount node.
     pub fn bind_mount_to(&self, dst_dentry: &Self, recursive: bool) -> Result<()> {
+        if self.mount_node.is_unbindable() {
+            return_errno_with_message!(Errno::EINVAL, "the mount is unbindable");
+        }
+
         let src_mount = self
             .mount_node
-            .clone_mount_node_tree(&self.inner, recursive);
+            .clone_mount_node_tree(&self.inner, recursive, false);
         src_mount.graft_mount_node_tree(dst_dentry)?;
         Ok(())
     }
 
+    /// Checks whether the file system can be modified through the mount of the `Dentry`.
+    ///
+    /// Returns [`Errno::EROFS`] if the mount is read-only.
+    pub fn check_writable_mount(&self) -> Result<()> {
+        if self.mount_node.flags().contains(PerMountFlags::RDONLY) {
+            return_errno_with_message!(Errno::EROFS, "the mount is read-only");
+        }
+        Ok(())
+    }
+
+    /// Checks whether children can be created in the directory of the `Dentry`.
+    ///
+    /// Returns [`Errno::EPERM`] if the directory is immutable.
+    fn check_creatable(&self) -> Result<()> {
+        if self.inode().flags().contains(InodeFlags::IMMUTABLE) {
+            return_errno_with_message!(Errno::EPERM, "the directory is immutable");
+        }
+        Ok(())
+    }
+
+    /// Checks whether the child named `name` can be deleted from the directory of the `Dentry`,
+    /// or be replaced by renaming.
+    ///
+    /// Returns [`Errno::EPERM`] if the directory or the child is immutable or append-only.
+    fn check_deletable(&self, name: &str) -> Result<()> {
+        // If the child does not exist, the error is left to the operation itself.
+        let Ok(child) = self.inode().lookup(name) else {
+            return Ok(());
+        };
+
+        let flags = InodeFlags::IMMUTABLE | InodeFlags::APPEND;
+        if self.inode().flags().intersects(flags) || child.flags().intersects(flags) {
+            return_errno_with_message!(Errno::EPERM, "the file is immutable or append-only");
+        }
+        Ok(())
+    }
+
+    /// Checks whether the `Dentry` is the root of its mount.
+    ///
+    /// Unlike the root of a file system, the root of a bind mount
+    /// can be any directory of the file system.
+    pub fn is_root_of_mount(&self) -> bool {
+        Arc::ptr_eq(&self.inner, self.mount_node.root_dentry())
+    }
+
     fn this(&self) -> Self {
         self.clone()
     }
 
+    pub(super) fn inner(&self) -> &Arc<Dentry_> {
+        &self.inner
+    }
+
     /// Gets the mount node of current `Dentry`.
     pub fn mount_node(&self) -> &Arc<MountNode> {
         &self.mount_node
     }
+
+    /// Updates the access time of the file after it is read.
+    ///
+    /// Whether the access time is updated depends on the flags of the mount.
+    pub fn touch_atime(&self) {
+        let mount_flags = self.mount_node.flags();
+        if mount_flags.contains(PerMountFlags::NOATIME)
+            || (mount_flags.contains(PerMountFlags::NODIRATIME) && self.type_() == InodeType::Dir)
+        {
+            return;
+        }
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let atime = self.atime();
+        if mount_flags.contains(PerMountFlags::RELATIME) {
+            // Like Linux, the access time is updated only if it is not newer than the
+            // modification or change time, or if it is more than one day old.
+            const RELATIME_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+            if atime > self.mtime()
+                && atime > self.ctime()
+                && now.saturating_sub(atime) < RELATIME_INTERVAL
+            {
+                return;
+            }
+        }
+        if atime != now {
+            self.set_atime(now);
+        }
+    }
 }
 
 #[inherit_methods(from = "self.inner")]
@@ -781,7 +1039,6 @@ impl Dentry {
     pub fn set_ctime(&self, time: Duration);
     pub fn key(&self) -> DentryKey;
     pub fn inode(&self) -> &Arc<dyn Inode>;
-    pub fn is_root_of_mount(&self) -> bool;
     pub fn is_mountpoint(&self) -> bool;
     pub fn set_xattr(
         &self,
@@ -3,8 +3,9 @@
 //! Form file paths within and across FSes with dentries and mount points.
 
 pub use dentry::{Dentry, DentryKey};
-pub use mount::MountNode;
+pub use mount::{MountNode, PerMountFlags, PropagationType};
 
+mod dcache;
 mod dentry;
 mod mount;
 
@@ -1,9 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
 use hashbrown::HashMap;
 
 use crate::{
     fs::{
+        notify::FsnotifyMarks,
         path::dentry::{Dentry, DentryKey, Dentry_},
         utils::{FileSystem, InodeType},
     },
@@ -12,6 +15,8 @@ use crate::{
 
 /// The `MountNode` is used to form a mount tree to maintain the mount information.
 pub struct MountNode {
+    /// The unique ID of the mount.
+    id: usize,
     /// Root dentry.
     root_dentry: Arc<Dentry_>,
     /// Mountpoint dentry. A mount node can be mounted on one dentry of another mount node,
@@ -23,6 +28,12 @@ pub struct MountNode {
     parent: RwLock<Option<Weak<MountNode>>>,
     /// Child mount nodes which are mounted on one dentry of self.
     children: RwLock<HashMap<DentryKey, Arc<Self>>>,
+    /// The file system notification marks on the mount.
+    fsnotify_marks: Arc<FsnotifyMarks>,
+    /// The per-mount flags.
+    flags: AtomicU32,
+    /// How the mount events are propagated from and to the mount.
+    propagation: SpinLock<Propagation>,
     /// Reference to self.
     this: Weak<Self>,
 }
@@ -49,11 +60,15 @@ impl MountNode {
     /// mount nodes must be explicitly assigned a mountpoint to maintain structural integrity.
     fn new(fs: Arc<dyn FileSystem>, parent_mount: Option<Weak<MountNode>>) -> Arc<Self> {
         Arc::new_cyclic(|weak_self| Self {
+            id: alloc_mount_id(),
             root_dentry: Dentry_::new_root(fs.root_inode()),
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(parent_mount),
             children: RwLock::new(HashMap::new()),
             fs,
+            fsnotify_marks: Arc::new(FsnotifyMarks::default()),
+            flags: AtomicU32::new(0),
+            propagation: SpinLock::new(Propagation::default()),
             this: weak_self.clone(),
         })
     }
@@ -68,8 +83,15 @@ impl MountNode {
     /// It is allowed to mount a fs even if the fs has been provided to another
     /// mountpoint. It is the fs's responsibility to ensure the data consistency.
     ///
+    /// If this mount node is shared, the new mount is propagated to the peers and the slaves.
+    ///
     /// Return the mounted child mount.
-    pub fn mount(&self, fs: Arc<dyn FileSystem>, mountpoint: &Dentry) -> Result<Arc<Self>> {
+    pub fn mount(
+        &self,
+        fs: Arc<dyn FileSystem>,
+        flags: PerMountFlags,
+        mountpoint: &Dentry,
+    ) -> Result<Arc<Self>> {
         if !Arc::ptr_eq(mountpoint.mount_node(), &self.this()) {
             return_errno_with_message!(Errno::EINVAL, "mountpoint not belongs to this");
         }
@@ -77,25 +99,50 @@ impl MountNode {
             return_errno!(Errno::ENOTDIR);
         }
 
-        let key = mountpoint.key();
-        let child_mount = Self::new(fs, Some(Arc::downgrade(mountpoint.mount_node())));
-        self.children.write().insert(key, child_mount.clone());
+        let child_mount = Self::new(fs, None);
+        child_mount.set_flags(flags);
+        self.attach_child(mountpoint.inner(), child_mount.clone());
         Ok(child_mount)
     }
 
     /// Unmounts a child mount node from the mountpoint and returns it.
     ///
     /// The mountpoint should belong to this mount node, or an error is returned.
+    ///
+    /// If this mount node is shared, the copies of the child mount that were propagated
+    /// to the peers and the slaves are unmounted as well, unless they have child mounts.
     pub fn unmount(&self, mountpoint: &Dentry) -> Result<Arc<Self>> {
         if !Arc::ptr_eq(mountpoint.mount_node(), &self.this()) {
             return_errno_with_message!(Errno::EINVAL, "mountpoint not belongs to this");
         }
 
         let child_mount = self
-            .children
-            .write()
-            .remove(&mountpoint.key())
+            .detach_child(mountpoint.inner())
             .ok_or_else(|| Error::with_message(Errno::ENOENT, "can not find child mount"))?;
+
+        let mut unmounted = vec![child_mount.clone()];
+        for (target, _) in self.propagation_targets() {
+            let Some(copy) = target.children.read().get(&mountpoint.key()).cloned() else {
+                continue;
+            };
+            if !copy.children.read().is_empty() || !copy.is_propagated_from(&child_mount) {
+                continue;
+            }
+            target.detach_child(mountpoint.inner());
+            unmounted.push(copy);
+        }
+
+        // The detached trees may be still in use, but they no longer take part in propagation.
+        for unmounted_root in unmounted {
+            let subtree = unmounted_root.subtree();
+            for mount in subtree.iter().skip(1) {
+                mount.mountpoint_dentry().unwrap().clear_mountpoint();
+            }
+            for mount in subtree {
+                mount.change_propagation(PropagationType::Private);
+            }
+        }
+
         Ok(child_mount)
     }
 
@@ -103,15 +150,49 @@ impl MountNode {
     ///
     /// The new mount node will have the same fs as the original one and
     /// have no parent and children. We should set the parent and children manually.
-    fn clone_mount_node(&self, root_dentry: &Arc<Dentry_>) -> Arc<Self> {
-        Arc::new_cyclic(|weak_self| Self {
+    ///
+    /// If `as_slave` is `false`, the new mount node joins the peer group of the original one
+    /// and receives the events from the same master, as a bind mount does.
+    /// Otherwise, it becomes a slave of the peer group of the original one
+    /// (or of its master if the original one is not shared).
+    fn clone_mount_node(&self, root_dentry: &Arc<Dentry_>, as_slave: bool) -> Arc<Self> {
+        let new_mount = Arc::new_cyclic(|weak_self| Self {
+            id: alloc_mount_id(),
             root_dentry: root_dentry.clone(),
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(None),
             children: RwLock::new(HashMap::new()),
             fs: self.fs.clone(),
+            fsnotify_marks: Arc::new(FsnotifyMarks::default()),
+            flags: AtomicU32::new(self.flags.load(Ordering::Relaxed)),
+            propagation: SpinLock::new(Propagation::default()),
             this: weak_self.clone(),
-        })
+        });
+
+        let propagation = self.propagation.lock();
+        let mut new_propagation = new_mount.propagation.lock();
+        if as_slave {
+            let master = propagation
+                .peer_group
+                .as_ref()
+                .or(propagation.master.as_ref());
+            if let Some(master) = master {
+                master.add_slave(&new_mount);
+                new_propagation.master = Some(master.clone());
+            }
+        } else {
+            if let Some(peer_group) = propagation.peer_group.as_ref() {
+                peer_group.add_peer(&new_mount);
+                new_propagation.peer_group = Some(peer_group.clone());
+            }
+            if let Some(master) = propagation.master.as_ref() {
+                master.add_slave(&new_mount);
+                new_propagation.master = Some(master.clone());
+            }
+        }
+        drop(new_propagation);
+
+        new_mount
     }
 
     /// Clones a mount tree starting from the specified root `Dentry_`.
@@ -120,14 +201,18 @@ impl MountNode {
     /// The new tree is a separate entity rooted at the given `Dentry_`,
     /// and the original tree remains unchanged.
     ///
-    /// If `recursive` is set to `true`, the entire tree will be copied.
+    /// If `recursive` is set to `true`, the entire tree will be copied,
+    /// except for the unbindable mounts and the mounts under them.
     /// Otherwise, only the root mount node will be copied.
+    ///
+    /// See [`Self::clone_mount_node`] for the meaning of `as_slave`.
     pub(super) fn clone_mount_node_tree(
         &self,
         root_dentry: &Arc<Dentry_>,
         recursive: bool,
+        as_slave: bool,
     ) -> Arc<Self> {
-        let new_root_mount = self.clone_mount_node(root_dentry);
+        let new_root_mount = self.clone_mount_node(root_dentry, as_slave);
         if !recursive {
             return new_root_mount;
         }
@@ -138,20 +223,17 @@ impl MountNode {
             let new_parent_mount = new_stack.pop().unwrap();
             let old_children = old_mount.children.read();
             for old_child_mount in old_children.values() {
+                if old_child_mount.is_unbindable() {
+                    continue;
+                }
                 let mountpoint_dentry = old_child_mount.mountpoint_dentry().unwrap();
-                if !mountpoint_dentry.is_descendant_of(old_mount.root_dentry()) {
+                if !new_parent_mount.contains(&mountpoint_dentry) {
                     continue;
                 }
                 let new_child_mount =
-                    old_child_mount.clone_mount_node(old_child_mount.root_dentry());
-                let key = mountpoint_dentry.key();
+                    old_child_mount.clone_mount_node(old_child_mount.root_dentry(), as_slave);
                 new_parent_mount
-                    .children
-                    .write()
-                    .insert(key, new_child_mount.clone());
-                new_child_mount.set_parent(&new_parent_mount);
-                new_child_mount
-                    .set_mountpoint_dentry(&old_child_mount.mountpoint_dentry().unwrap());
+                    .attach_child_without_propagation(&mountpoint_dentry, new_child_mount.clone());
                 stack.push(old_child_mount.clone());
                 new_stack.push(new_child_mount);
             }
@@ -160,36 +242,64 @@ impl MountNode {
         new_root_mount
     }
 
-    /// Detaches the mount node from the parent mount node.
-    fn detach_mount_node(&self) {
-        if let Some(parent) = self.parent() {
-            let parent = parent.upgrade().unwrap();
-            parent
-                .children
-                .write()
-                .remove(&self.mountpoint_dentry().unwrap().key());
+    /// Attaches a child mount node to the mountpoint and propagates it
+    /// to the peers and the slaves of this mount node.
+    ///
+    /// The mountpoint should belong to this mount node.
+    fn attach_child(&self, mountpoint: &Arc<Dentry_>, child_mount: Arc<Self>) {
+        let targets = self.propagation_targets();
+
+        // Like Linux, the mounts attached under a shared mount become shared,
+        // so that the copies propagated to the peers are peers of them.
+        if self.is_shared() {
+            for mount in child_mount.subtree() {
+                if !mount.is_shared() {
+                    mount.change_propagation(PropagationType::Shared);
+                }
+            }
+        }
+
+        self.attach_child_without_propagation(mountpoint, child_mount.clone());
+
+        for (target, is_peer) in targets {
+            if !target.contains(mountpoint) {
+                continue;
+            }
+            let copy = child_mount.clone_mount_node_tree(child_mount.root_dentry(), true, !is_peer);
+            target.attach_child_without_propagation(mountpoint, copy);
         }
     }
 
-    /// Attaches the mount node to the mountpoint.
-    fn attach_mount_node(&self, mountpoint: &Dentry) {
-        let key = mountpoint.key();
-        mountpoint
-            .mount_node()
-            .children
-            .write()
-            .insert(key, self.this());
-        self.set_parent(mountpoint.mount_node());
-        mountpoint.set_mountpoint(self.this());
+    /// Attaches a child mount node to the mountpoint.
+    fn attach_child_without_propagation(&self, mountpoint: &Arc<Dentry_>, child_mount: Arc<Self>) {
+        child_mount.set_parent(&self.this());
+        child_mount.set_mountpoint_dentry(mountpoint);
+        mountpoint.set_mountpoint_dentry();
+        self.children.write().insert(mountpoint.key(), child_mount);
+    }
+
+    /// Detaches the child mount node from the mountpoint and returns it.
+    fn detach_child(&self, mountpoint: &Arc<Dentry_>) -> Option<Arc<Self>> {
+        let child_mount = self.children.write().remove(&mountpoint.key())?;
+        mountpoint.clear_mountpoint();
+        Some(child_mount)
     }
 
     /// Grafts the mount node tree to the mountpoint.
+    ///
+    /// The tree is detached from its parent first if it has one, as a move mount does.
     pub fn graft_mount_node_tree(&self, mountpoint: &Dentry) -> Result<()> {
         if mountpoint.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
-        self.detach_mount_node();
-        self.attach_mount_node(mountpoint);
+
+        if let Some(parent) = self.parent() {
+            let parent = parent.upgrade().unwrap();
+            parent.detach_child(&self.mountpoint_dentry().unwrap());
+        }
+        mountpoint
+            .mount_node()
+            .attach_child(mountpoint.inner(), self.this());
         Ok(())
     }
 
@@ -201,6 +311,11 @@ impl MountNode {
         self.children.read().get(&mountpoint.key()).cloned()
     }
 
+    /// Gets the unique ID of this mount node.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
     /// Gets the root `Dentry_` of this mount node.
     pub fn root_dentry(&self) -> &Arc<Dentry_> {
         &self.root_dentry
@@ -256,14 +371,301 @@ impl MountNode {
     pub fn fs(&self) -> &Arc<dyn FileSystem> {
         &self.fs
     }
+
+    /// Gets the file system notification marks on the mount.
+    pub fn fsnotify_marks(&self) -> &Arc<FsnotifyMarks> {
+        &self.fsnotify_marks
+    }
+
+    /// Gets the per-mount flags.
+    pub fn flags(&self) -> PerMountFlags {
+        PerMountFlags::from_bits_truncate(self.flags.load(Ordering::Relaxed))
+    }
+
+    /// Sets the per-mount flags.
+    pub fn set_flags(&self, flags: PerMountFlags) {
+        self.flags.store(flags.bits(), Ordering::Relaxed);
+    }
+
+    /// Changes the propagation type of the mount.
+    ///
+    /// If `recursive` is `true`, the type of all the mounts in the mount tree is changed.
+    pub fn set_propagation(&self, type_: PropagationType, recursive: bool) {
+        if !recursive {
+            self.change_propagation(type_);
+            return;
+        }
+        for mount in self.subtree() {
+            mount.change_propagation(type_);
+        }
+    }
+
+    /// Returns the ID of the peer group if the mount is shared.
+    pub fn peer_group_id(&self) -> Option<usize> {
+        let propagation = self.propagation.lock();
+        propagation
+            .peer_group
+            .as_ref()
+            .map(|peer_group| peer_group.id)
+    }
+
+    /// Returns the ID of the peer group that the mount receives the events from
+    /// if the mount is a slave.
+    pub fn master_id(&self) -> Option<usize> {
+        let propagation = self.propagation.lock();
+        propagation.master.as_ref().map(|master| master.id)
+    }
+
+    /// Returns whether the mount is shared.
+    pub fn is_shared(&self) -> bool {
+        self.propagation.lock().peer_group.is_some()
+    }
+
+    /// Returns whether the mount is unbindable.
+    pub fn is_unbindable(&self) -> bool {
+        self.propagation.lock().is_unbindable
+    }
+
+    fn change_propagation(&self, type_: PropagationType) {
+        let this = self.this();
+        let mut propagation = self.propagation.lock();
+
+        match type_ {
+            PropagationType::Shared => {
+                if propagation.peer_group.is_none() {
+                    let peer_group = PeerGroup::new();
+                    peer_group.add_peer(&this);
+                    propagation.peer_group = Some(peer_group);
+                }
+                propagation.is_unbindable = false;
+            }
+            PropagationType::Slave => {
+                // Like Linux, a shared mount becomes a slave of its peer group
+                // only if there are other peers. Otherwise, it keeps its master if any.
+                if let Some(peer_group) = propagation.peer_group.take() {
+                    peer_group.remove(self);
+                    if peer_group.has_peers() {
+                        if let Some(master) = propagation.master.take() {
+                            master.remove(self);
+                        }
+                        peer_group.add_slave(&this);
+                        propagation.master = Some(peer_group);
+                    }
+                }
+                propagation.is_unbindable = false;
+            }
+            PropagationType::Private | PropagationType::Unbindable => {
+                if let Some(peer_group) = propagation.peer_group.take() {
+                    peer_group.remove(self);
+                }
+                if let Some(master) = propagation.master.take() {
+                    master.remove(self);
+                }
+                propagation.is_unbindable = type_ == PropagationType::Unbindable;
+            }
+        }
+    }
+
+    /// Returns the mounts that the mount events of this mount are propagated to,
+    /// each with whether it is a peer (or a slave otherwise).
+    fn propagation_targets(&self) -> Vec<(Arc<Self>, bool)> {
+        let Some(peer_group) = self.propagation.lock().peer_group.clone() else {
+            return Vec::new();
+        };
+
+        let mut targets = Vec::new();
+        let mut visited_mounts = BTreeSet::from([self.id]);
+        let mut visited_groups = BTreeSet::from([peer_group.id]);
+
+        for peer in peer_group.peers() {
+            if visited_mounts.insert(peer.id) {
+                targets.push((peer, true));
+            }
+        }
+
+        // The events received by a slave are propagated further if the slave is shared.
+        let mut groups = vec![peer_group];
+        while let Some(group) = groups.pop() {
+            for slave in group.slaves() {
+                let slave_group = slave.propagation.lock().peer_group.clone();
+                if visited_mounts.insert(slave.id) {
+                    targets.push((slave, false));
+                }
+                let Some(slave_group) = slave_group else {
+                    continue;
+                };
+                if !visited_groups.insert(slave_group.id) {
+                    continue;
+                }
+                for peer in slave_group.peers() {
+                    if visited_mounts.insert(peer.id) {
+                        targets.push((peer, false));
+                    }
+                }
+                groups.push(slave_group);
+            }
+        }
+
+        targets
+    }
+
+    /// Returns whether this mount is a copy of `mount` propagated by a mount event,
+    /// i.e., it is a peer or a slave of `mount`.
+    fn is_propagated_from(&self, mount: &Self) -> bool {
+        let Some(peer_group) = mount.propagation.lock().peer_group.clone() else {
+            return false;
+        };
+        let propagation = self.propagation.lock();
+        [propagation.peer_group.as_ref(), propagation.master.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|group| Arc::ptr_eq(group, &peer_group))
+    }
+
+    /// Returns whether the `Dentry_` can be reached from the root of this mount.
+    fn contains(&self, dentry: &Arc<Dentry_>) -> bool {
+        Arc::ptr_eq(dentry, &self.root_dentry) || dentry.is_descendant_of(&self.root_dentry)
+    }
+
+    /// Returns all the mounts in the mount tree rooted at this mount, starting from this mount.
+    fn subtree(&self) -> Vec<Arc<Self>> {
+        let mut mounts = vec![self.this()];
+        let mut index = 0;
+        while let Some(mount) = mounts.get(index).cloned() {
+            mounts.extend(mount.children.read().values().cloned());
+            index += 1;
+        }
+        mounts
+    }
+}
+
+/// The propagation type of a mount.
+///
+/// Reference: <https://docs.kernel.org/filesystems/sharedsubtree.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropagationType {
+    /// The mount events are propagated to and from the peers of the mount.
+    Shared,
+    /// The mount events are neither propagated to nor from the mount.
+    Private,
+    /// The mount events are propagated from the master of the mount, but not back.
+    Slave,
+    /// The mount is private and cannot be bind mounted.
+    Unbindable,
+}
+
+#[derive(Default)]
+struct Propagation {
+    /// The peer group that the mount belongs to if the mount is shared.
+    peer_group: Option<Arc<PeerGroup>>,
+    /// The peer group that the mount receives the events from if the mount is a slave.
+    master: Option<Arc<PeerGroup>>,
+    is_unbindable: bool,
+}
+
+/// A group of shared mounts that propagate the mount events to each other
+/// and to their slaves.
+struct PeerGroup {
+    id: usize,
+    peers: SpinLock<Vec<Weak<MountNode>>>,
+    slaves: SpinLock<Vec<Weak<MountNode>>>,
+}
+
+impl PeerGroup {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            id: alloc_peer_group_id(),
+            peers: SpinLock::new(Vec::new()),
+            slaves: SpinLock::new(Vec::new()),
+        })
+    }
+
+    fn add_peer(&self, mount: &Arc<MountNode>) {
+        self.peers.lock().push(Arc::downgrade(mount));
+    }
+
+    fn add_slave(&self, mount: &Arc<MountNode>) {
+        self.slaves.lock().push(Arc::downgrade(mount));
+    }
+
+    /// Removes a mount from the peers or the slaves.
+    ///
+    /// The dropped mounts are removed as well.
+    fn remove(&self, mount: &MountNode) {
+        let is_kept = |member: &Weak<MountNode>| {
+            member.strong_count() > 0 && !core::ptr::eq(member.as_ptr(), mount)
+        };
+        self.peers.lock().retain(is_kept);
+        self.slaves.lock().retain(is_kept);
+    }
+
+    fn has_peers(&self) -> bool {
+        self.peers.lock().iter().any(|peer| peer.strong_count() > 0)
+    }
+
+    fn peers(&self) -> Vec<Arc<MountNode>> {
+        self.peers.lock().iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn slaves(&self) -> Vec<Arc<MountNode>> {
+        self.slaves
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+}
+
+bitflags! {
+    /// The per-mount flags.
+    ///
+    /// The values are the same as the `ST_*` flags reported by `statfs` in Linux. Currently, only
+    /// `RDONLY` and the flags on access times are enforced. The others are only reported.
+    pub struct PerMountFlags: u32 {
+        /// The mount is read-only.
+        const RDONLY = 1 << 0;
+        /// The set-user-ID and set-group-ID bits are ignored.
+        const NOSUID = 1 << 1;
+        /// The device special files cannot be accessed.
+        const NODEV = 1 << 2;
+        /// The programs cannot be executed.
+        const NOEXEC = 1 << 3;
+        /// The writes are synced at once.
+        const SYNCHRONOUS = 1 << 4;
+        /// The mandatory locks are allowed.
+        const MANDLOCK = 1 << 6;
+        /// The access times are not updated.
+        const NOATIME = 1 << 10;
+        /// The access times of directories are not updated.
+        const NODIRATIME = 1 << 11;
+        /// The access times are updated relative to the modification or change times.
+        const RELATIME = 1 << 12;
+    }
 }
 
 impl Debug for MountNode {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("MountNode")
+            .field("id", &self.id)
             .field("root", &self.root_dentry)
             .field("mountpoint", &self.mountpoint_dentry)
             .field("fs", &self.fs)
             .finish()
     }
 }
+
+/// Allocates a unique ID for a mount node.
+///
+/// The IDs start from one, as in Linux.
+fn alloc_mount_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Allocates a unique ID for a peer group.
+///
+/// The IDs start from one, as in Linux.
+fn alloc_peer_group_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
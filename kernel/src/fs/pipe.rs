@@ -14,6 +14,7 @@ use crate::{
         Gid, Uid,
     },
     time::clocks::RealTimeCoarseClock,
+    util::{MultiRead, MultiWrite},
 };
 
 const DEFAULT_PIPE_BUF_SIZE: usize = 65536;
@@ -50,6 +51,55 @@ impl PipeReader {
             status_flags: AtomicU32::new(status_flags.bits()),
         }))
     }
+
+    /// Reads from the pipe to `writer`.
+    ///
+    /// Unlike [`FileLike::read`], the data can be read to multiple buffers at once (e.g., by
+    /// `vmsplice`), and `is_nonblocking` forces a non-blocking read.
+    pub fn read_to(&self, writer: &mut dyn MultiWrite, is_nonblocking: bool) -> Result<usize> {
+        if is_nonblocking || self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+            self.consumer.try_read(writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || self.consumer.try_read(writer))
+        }
+    }
+
+    /// Reads at most `max_len` bytes from the pipe with `read_fn`.
+    ///
+    /// This moves data from the pipe buffer without an intermediate buffer (e.g., by `splice`).
+    /// See [`Consumer::try_read_with`] for the usage of `read_fn`.
+    pub fn read_with(
+        &self,
+        max_len: usize,
+        is_nonblocking: bool,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        if is_nonblocking || self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+            self.consumer.try_read_with(max_len, read_fn)
+        } else {
+            self.wait_events(IoEvents::IN, None, || {
+                self.consumer.try_read_with(max_len, read_fn)
+            })
+        }
+    }
+
+    /// Reads at most `max_len` bytes from the pipe with `read_fn`, without consuming them.
+    ///
+    /// This is used to duplicate the data in the pipe (e.g., by `tee`).
+    pub fn peek_with(
+        &self,
+        max_len: usize,
+        is_nonblocking: bool,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        if is_nonblocking || self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+            self.consumer.try_peek_with(max_len, read_fn)
+        } else {
+            self.wait_events(IoEvents::IN, None, || {
+                self.consumer.try_peek_with(max_len, read_fn)
+            })
+        }
+    }
 }
 
 impl Pollable for PipeReader {
@@ -120,6 +170,37 @@ impl PipeWriter {
             status_flags: AtomicU32::new(status_flags.bits()),
         }))
     }
+
+    /// Writes to the pipe from `reader`.
+    ///
+    /// Unlike [`FileLike::write`], the data can be written from multiple buffers at once (e.g.,
+    /// by `vmsplice`), and `is_nonblocking` forces a non-blocking write.
+    pub fn write_from(&self, reader: &mut dyn MultiRead, is_nonblocking: bool) -> Result<usize> {
+        if is_nonblocking || self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+            self.producer.try_write(reader)
+        } else {
+            self.wait_events(IoEvents::OUT, None, || self.producer.try_write(reader))
+        }
+    }
+
+    /// Writes at most `max_len` bytes to the pipe with `write_fn`.
+    ///
+    /// This moves data to the pipe buffer without an intermediate buffer (e.g., by `splice`).
+    /// See [`Producer::try_write_with`] for the usage of `write_fn`.
+    pub fn write_with(
+        &self,
+        max_len: usize,
+        is_nonblocking: bool,
+        write_fn: &mut dyn FnMut(&mut VmWriter) -> Result<usize>,
+    ) -> Result<usize> {
+        if is_nonblocking || self.status_flags().contains(StatusFlags::O_NONBLOCK) {
+            self.producer.try_write_with(max_len, write_fn)
+        } else {
+            self.wait_events(IoEvents::OUT, None, || {
+                self.producer.try_write_with(max_len, write_fn)
+            })
+        }
+    }
 }
 
 impl Pollable for PipeWriter {
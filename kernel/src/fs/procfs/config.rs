@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/config` file support, which tells the user space
+//! about the configuration that the kernel is built with.
+//!
+//! Unlike `/proc/config.gz` in Linux, the file is not compressed.
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    kconfig::KCONFIG,
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/config`.
+pub struct ConfigFileOps;
+
+impl ConfigFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for ConfigFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::from("#\n# Asterinas kernel configuration\n#\n");
+        if KCONFIG.is_empty() {
+            output.push_str("# The kernel is not built with the OSDK configuration\n");
+        } else {
+            output.push_str(KCONFIG);
+        }
+        Ok(output.into_bytes())
+    }
+}
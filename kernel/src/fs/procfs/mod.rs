@@ -5,9 +5,11 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use filesystems::{FileSystemType, FILESYSTEM_TYPES};
 
 use self::{
+    config::ConfigFileOps,
     cpuinfo::CpuInfoFileOps,
     loadavg::LoadAvgFileOps,
     meminfo::MemInfoFileOps,
+    net::NetDirOps,
     pid::PidDirOps,
     self_::SelfSymOps,
     sys::SysDirOps,
@@ -27,10 +29,12 @@ use crate::{
     },
 };
 
+mod config;
 mod cpuinfo;
 mod filesystems;
 mod loadavg;
 mod meminfo;
+mod net;
 mod pid;
 mod self_;
 mod sys;
@@ -43,8 +47,18 @@ pub(super) fn init() {
             FileSystemType::new("proc", true),
             FileSystemType::new("ramfs", true),
             FileSystemType::new("devpts", true),
+            FileSystemType::new("devtmpfs", true),
+            FileSystemType::new("fuse", true),
+            FileSystemType::new("virtiofs", true),
+            FileSystemType::new("9p", true),
             FileSystemType::new("ext2", false),
+            FileSystemType::new("ext3", false),
+            FileSystemType::new("ext4", false),
             FileSystemType::new("exfat", false),
+            FileSystemType::new("vfat", false),
+            FileSystemType::new("msdos", false),
+            FileSystemType::new("iso9660", false),
+            FileSystemType::new("squashfs", false),
         ]
     });
 }
@@ -122,6 +136,8 @@ impl DirOps for RootDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let child = if name == "self" {
             SelfSymOps::new_inode(this_ptr.clone())
+        } else if name == "net" {
+            NetDirOps::new_inode(this_ptr.clone())
         } else if name == "sys" {
             SysDirOps::new_inode(this_ptr.clone())
         } else if name == "thread-self" {
@@ -134,6 +150,8 @@ impl DirOps for RootDirOps {
             LoadAvgFileOps::new_inode(this_ptr.clone())
         } else if name == "cpuinfo" {
             CpuInfoFileOps::new_inode(this_ptr.clone())
+        } else if name == "config" {
+            ConfigFileOps::new_inode(this_ptr.clone())
         } else if let Ok(pid) = name.parse::<Pid>() {
             let process_ref =
                 process_table::get_process(pid).ok_or_else(|| Error::new(Errno::ENOENT))?;
@@ -154,6 +172,7 @@ impl DirOps for RootDirOps {
         cached_children.put_entry_if_not_found("thread-self", || {
             ThreadSelfSymOps::new_inode(this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("net", || NetDirOps::new_inode(this_ptr.clone()));
         cached_children.put_entry_if_not_found("sys", || SysDirOps::new_inode(this_ptr.clone()));
         cached_children.put_entry_if_not_found("filesystems", || {
             FileSystemsFileOps::new_inode(this_ptr.clone())
@@ -164,6 +183,8 @@ impl DirOps for RootDirOps {
             .put_entry_if_not_found("loadavg", || LoadAvgFileOps::new_inode(this_ptr.clone()));
         cached_children
             .put_entry_if_not_found("cpuinfo", || CpuInfoFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("config", || ConfigFileOps::new_inode(this_ptr.clone()));
         for process in process_table::process_table_mut().iter() {
             let pid = process.pid().to_string();
             cached_children.put_entry_if_not_found(&pid, || {
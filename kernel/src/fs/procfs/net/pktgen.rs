@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The control files of pktgen, which are at `/proc/net/pktgen`.
+//!
+//! - `pgctrl` starts, stops, and resets pktgen.
+//! - `kpktgend_0` adds and removes the devices.
+//! - A file named after each added device configures the device and reports its result.
+
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    net::pktgen,
+    prelude::*,
+};
+
+/// The mode of the control files, which is the same as Linux.
+const CTRL_FILE_MODE: u16 = 0o600;
+
+/// Represents the inode at `/proc/net/pktgen`.
+pub struct PktgenDirOps;
+
+impl PktgenDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        // The children change when the devices are added or removed.
+        ProcDirBuilder::new(Self)
+            .parent(parent)
+            .volatile()
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for PktgenDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "pgctrl" => new_ctrl_inode(PgctrlFileOps, this_ptr),
+            "kpktgend_0" => new_ctrl_inode(ThreadFileOps, this_ptr),
+            _ if pktgen::get_device(name).is_some() => {
+                new_ctrl_inode(DeviceFileOps(name.to_string()), this_ptr)
+            }
+            _ => return_errno!(Errno::ENOENT),
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<PktgenDirOps>>().unwrap().this()
+        };
+        let devices = pktgen::devices();
+        let mut cached_children = this.cached_children().write();
+
+        let removed_names: Vec<String> = cached_children
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                name.as_str() != "pgctrl"
+                    && name.as_str() != "kpktgend_0"
+                    && devices.iter().all(|device| device.name() != name.as_str())
+            })
+            .cloned()
+            .collect();
+        for name in removed_names {
+            cached_children.remove_entry_by_name(&name);
+        }
+
+        cached_children
+            .put_entry_if_not_found("pgctrl", || new_ctrl_inode(PgctrlFileOps, this_ptr.clone()));
+        cached_children.put_entry_if_not_found("kpktgend_0", || {
+            new_ctrl_inode(ThreadFileOps, this_ptr.clone())
+        });
+        for device in devices.iter() {
+            cached_children.put_entry_if_not_found(device.name(), || {
+                new_ctrl_inode(DeviceFileOps(device.name().to_string()), this_ptr.clone())
+            });
+        }
+    }
+}
+
+fn new_ctrl_inode<O: FileOps + 'static>(file: O, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+    ProcFileBuilder::new(file)
+        .parent(parent)
+        .mode(InodeMode::from_bits_truncate(CTRL_FILE_MODE))
+        .build()
+        .unwrap()
+}
+
+/// Reads a command written to a control file.
+fn read_command(reader: &mut VmReader) -> Result<(String, usize)> {
+    let buf = reader.collect()?;
+    let len = buf.len();
+    let command = String::from_utf8(buf)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the command is not valid UTF-8"))?;
+    Ok((command.trim().to_string(), len))
+}
+
+/// Represents the inode at `/proc/net/pktgen/pgctrl`.
+struct PgctrlFileOps;
+
+impl FileOps for PgctrlFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let running = pktgen::devices()
+            .iter()
+            .filter(|device| device.is_running())
+            .count();
+        let output = format!(
+            "Packet Generator (proc) version 2.75\nRunning: {} device(s)\n",
+            running
+        );
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (command, len) = read_command(reader)?;
+        match command.as_str() {
+            "start" => pktgen::start()?,
+            "stop" => pktgen::stop(),
+            "reset" => pktgen::reset(),
+            _ => return_errno_with_message!(Errno::EINVAL, "the command is not supported"),
+        }
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `/proc/net/pktgen/kpktgend_0`.
+///
+/// Linux has a sending thread per CPU, but pktgen has a sending thread per device, so all the
+/// devices are managed by this single file.
+struct ThreadFileOps;
+
+impl FileOps for ThreadFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let devices = pktgen::devices();
+        let names = |is_running: bool| {
+            devices
+                .iter()
+                .filter(|device| device.is_running() == is_running)
+                .map(|device| format!("{} ", device.name()))
+                .collect::<String>()
+        };
+        let output = format!("Running: {}\nStopped: {}\n", names(true), names(false));
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (command, len) = read_command(reader)?;
+        let mut args = command.split_whitespace();
+        match (args.next(), args.next()) {
+            (Some("add_device"), Some(name)) => pktgen::add_device(name)?,
+            (Some("rem_device_all"), None) => pktgen::remove_all_devices()?,
+            _ => return_errno_with_message!(Errno::EINVAL, "the command is not supported"),
+        }
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `/proc/net/pktgen/<device>`.
+struct DeviceFileOps(String);
+
+impl DeviceFileOps {
+    fn device(&self) -> Result<Arc<pktgen::PktgenDevice>> {
+        pktgen::get_device(&self.0)
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "the device is removed"))
+    }
+}
+
+impl FileOps for DeviceFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.device()?.report().into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (command, len) = read_command(reader)?;
+        self.device()?.apply_command(&command)?;
+        Ok(len)
+    }
+}
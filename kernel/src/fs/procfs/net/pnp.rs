@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `/proc/net/pnp` file, which reports the result of IP autoconfiguration at boot time.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/nfs/nfsroot.html>.
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    net::ipconfig,
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/net/pnp`.
+pub struct PnpFileOps;
+
+impl PnpFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for PnpFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(ipconfig::pnp_report().into_bytes())
+    }
+}
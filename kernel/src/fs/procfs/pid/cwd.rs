@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::{ProcSymBuilder, SymOps},
+        utils::Inode,
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/cwd`.
+pub struct CwdSymOps(Arc<Process>);
+
+impl CwdSymOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcSymBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl SymOps for CwdSymOps {
+    fn read_link(&self) -> Result<String> {
+        let main_thread = self.0.main_thread();
+        let fs = main_thread.as_posix_thread().unwrap().fs().clone();
+        let cwd = fs.resolver().read().cwd().abs_path();
+        Ok(cwd)
+    }
+
+    fn is_magic_link(&self) -> bool {
+        true
+    }
+}
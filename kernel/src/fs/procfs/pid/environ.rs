@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::check_read_access;
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/environ`.
+///
+/// The environment variables are separated by the null bytes, just like the
+/// ones on the initial user stack.
+pub struct EnvironFileOps(Arc<Process>);
+
+impl EnvironFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o400))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for EnvironFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        check_read_access(&self.0)?;
+
+        if self.0.status().is_zombie() {
+            return Ok(Vec::new());
+        }
+        let Ok(envp_cstrs) = self.0.vm().init_stack_reader().envp() else {
+            return Ok(Vec::new());
+        };
+        let environ_output = envp_cstrs
+            .into_iter()
+            .flat_map(|c_str| c_str.into_bytes_with_nul().into_iter())
+            .collect();
+        Ok(environ_output)
+    }
+}
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::format;
+
 use crate::{
     fs::{
         file_handle::FileLike,
@@ -8,7 +10,7 @@ use crate::{
         procfs::{
             pid::FdEvents, DirOps, Observer, ProcDir, ProcDirBuilder, ProcSymBuilder, SymOps,
         },
-        utils::{DirEntryVecExt, Inode},
+        utils::{DirEntryVecExt, Inode, InodeType},
     },
     prelude::*,
     process::posix_thread::AsPosixThread,
@@ -108,12 +110,23 @@ impl FileSymOps {
 
 impl SymOps for FileSymOps {
     fn read_link(&self) -> Result<String> {
-        let path = if let Some(inode_handle) = self.0.downcast_ref::<InodeHandle>() {
-            inode_handle.dentry().abs_path()
+        if let Some(inode_handle) = self.0.downcast_ref::<InodeHandle>() {
+            return Ok(inode_handle.dentry().abs_path());
+        }
+
+        // The files that are not in any file system are named after their types, like Linux.
+        let path = if self.0.as_socket().is_some() {
+            format!("socket:[{}]", self.0.metadata().ino)
+        } else if self.0.metadata().type_ == InodeType::NamedPipe {
+            format!("pipe:[{}]", self.0.metadata().ino)
         } else {
             // TODO: get the real path for other FileLike object
             String::from("/dev/tty")
         };
         Ok(path)
     }
+
+    fn is_magic_link(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        file_table::{FdFlags, FileDesc},
+        inode_handle::InodeHandle,
+        procfs::{
+            pid::FdEvents,
+            template::{FileOps, ProcFileBuilder},
+            DirOps, Observer, ProcDir, ProcDirBuilder,
+        },
+        utils::{CreationFlags, DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/fdinfo`.
+pub struct FdInfoDirOps(Arc<Process>);
+
+impl FdInfoDirOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let main_thread = process_ref.main_thread();
+        let file_table = main_thread.as_posix_thread().unwrap().file_table();
+
+        let fdinfo_inode = ProcDirBuilder::new(Self(process_ref.clone()))
+            .parent(parent)
+            .build()
+            .unwrap();
+        file_table
+            .lock()
+            .as_ref()
+            .unwrap()
+            .read()
+            .register_observer(Arc::downgrade(&fdinfo_inode) as _);
+
+        fdinfo_inode
+    }
+}
+
+impl Observer<FdEvents> for ProcDir<FdInfoDirOps> {
+    fn on_events(&self, events: &FdEvents) {
+        let fd_string = if let FdEvents::Close(fd) = events {
+            fd.to_string()
+        } else {
+            return;
+        };
+
+        let mut cached_children = self.cached_children().write();
+        cached_children.remove_entry_by_name(&fd_string);
+    }
+}
+
+impl DirOps for FdInfoDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let main_thread = self.0.main_thread();
+        let file_table = main_thread.as_posix_thread().unwrap().file_table().lock();
+        let file_table = file_table
+            .as_ref()
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+
+        let fd = name
+            .parse::<FileDesc>()
+            .map_err(|_| Error::new(Errno::ENOENT))?;
+        file_table
+            .read()
+            .get_entry(fd)
+            .map_err(|_| Error::new(Errno::ENOENT))?;
+
+        Ok(FdInfoFileOps::new_inode(self.0.clone(), fd, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let main_thread = self.0.main_thread();
+        let file_table = main_thread.as_posix_thread().unwrap().file_table().lock();
+        let Some(file_table) = file_table.as_ref() else {
+            return;
+        };
+
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<FdInfoDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+
+        for (fd, _) in file_table.read().fds_and_files() {
+            cached_children.put_entry_if_not_found(&fd.to_string(), || {
+                FdInfoFileOps::new_inode(self.0.clone(), fd, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/[pid]/fdinfo/N`.
+///
+/// The file is generated when it is read, because the offset and the flags of
+/// the file descriptor may change after the inode is created.
+struct FdInfoFileOps {
+    process: Arc<Process>,
+    fd: FileDesc,
+}
+
+impl FdInfoFileOps {
+    pub fn new_inode(
+        process: Arc<Process>,
+        fd: FileDesc,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self { process, fd })
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o400))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for FdInfoFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let main_thread = self.process.main_thread();
+        let file_table = main_thread.as_posix_thread().unwrap().file_table().lock();
+        let file_table = file_table
+            .as_ref()
+            .ok_or_else(|| Error::new(Errno::ENOENT))?
+            .read();
+        let entry = file_table
+            .get_entry(self.fd)
+            .map_err(|_| Error::new(Errno::ENOENT))?;
+        let file = entry.file();
+
+        let mut flags = file.status_flags().bits() | file.access_mode() as u32;
+        if entry.flags().contains(FdFlags::CLOEXEC) {
+            flags |= CreationFlags::O_CLOEXEC.bits();
+        }
+        let (pos, mnt_id) = if let Some(inode_handle) = file.downcast_ref::<InodeHandle>() {
+            (
+                inode_handle.offset(),
+                inode_handle.dentry().mount_node().id(),
+            )
+        } else {
+            (0, 0)
+        };
+
+        let mut fdinfo_output = String::new();
+        writeln!(fdinfo_output, "pos:\t{}", pos).unwrap();
+        writeln!(fdinfo_output, "flags:\t0{:o}", flags).unwrap();
+        writeln!(fdinfo_output, "mnt_id:\t{}", mnt_id).unwrap();
+        writeln!(fdinfo_output, "ino:\t{}", file.metadata().ino).unwrap();
+        Ok(fdinfo_output.into_bytes())
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use super::check_read_access;
+use crate::{
+    fs::{
+        device::DeviceId,
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    vm::{
+        perms::VmPerms,
+        vmar::vm_mapping::{MappingName, VmMappingInfo},
+    },
+    Process,
+};
+
+/// The column where the names of the mappings start, which is the same as Linux.
+const NAME_COLUMN: usize = 25 + size_of::<usize>() * 6 - 1;
+
+/// Represents the inode at `/proc/[pid]/maps`.
+/// See https://github.com/torvalds/linux/blob/ce1c54fdff7c4556b08f5b875a331d8952e8b6b7/fs/proc/task_mmu.c#L304
+///
+/// Each line describes a mapping with the following fields:
+/// - address:  The start and the end addresses of the mapping.
+/// - perms:    The permissions, and whether the mapping is shared (s) or private (p).
+/// - offset:   The offset of the mapping in the file.
+/// - dev:      The major and minor device numbers of the file.
+/// - inode:    The inode number of the file.
+/// - pathname: The path of the file, or `[heap]`, `[stack]` and `[vdso]`.
+pub struct MapsFileOps(Arc<Process>);
+
+impl MapsFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MapsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        check_read_access(&self.0)?;
+
+        // The process may exit at any time, after which its VMAR is dropped.
+        let Some(mappings) = self
+            .0
+            .lock_root_vmar()
+            .as_ref()
+            .map(|vmar| vmar.mappings_info())
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut maps_output = String::new();
+        for mapping in mappings {
+            write_mapping(&mut maps_output, &mapping);
+        }
+        Ok(maps_output.into_bytes())
+    }
+}
+
+fn write_mapping(output: &mut String, mapping: &VmMappingInfo) {
+    let line_start = output.len();
+
+    let perm = |perm: VmPerms, c: char| if mapping.perms.contains(perm) { c } else { '-' };
+    let (offset, dev, ino, name) = match &mapping.name {
+        Some(MappingName::File(dentry)) => {
+            let metadata = dentry.inode().metadata();
+            (
+                mapping.vmo_offset,
+                DeviceId::from(metadata.dev),
+                metadata.ino,
+                Some(dentry.abs_path()),
+            )
+        }
+        Some(MappingName::Heap) => (0, DeviceId::from(0), 0, Some(String::from("[heap]"))),
+        Some(MappingName::Stack) => (0, DeviceId::from(0), 0, Some(String::from("[stack]"))),
+        Some(MappingName::Vdso) => (0, DeviceId::from(0), 0, Some(String::from("[vdso]"))),
+        None => (0, DeviceId::from(0), 0, None),
+    };
+
+    write!(
+        output,
+        "{:08x}-{:08x} {}{}{}{} {:08x} {:02x}:{:02x} {} ",
+        mapping.range.start,
+        mapping.range.end,
+        perm(VmPerms::READ, 'r'),
+        perm(VmPerms::WRITE, 'w'),
+        perm(VmPerms::EXEC, 'x'),
+        if mapping.is_shared { 's' } else { 'p' },
+        offset,
+        dev.major(),
+        dev.minor(),
+        ino,
+    )
+    .unwrap();
+
+    if let Some(name) = name {
+        let width = output.len() - line_start;
+        output.extend(core::iter::repeat_n(' ', NAME_COLUMN.saturating_sub(width)));
+        output.push(' ');
+        output.push_str(&name);
+    }
+    output.push('\n');
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_rights::ReadOp;
+use ostd::task::Task;
+
+use super::check_ptrace_access;
+use crate::{
+    context::CurrentUserSpace,
+    events::IoEvents,
+    fs::{
+        inode_handle::FileIo,
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+        Credentials,
+    },
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/mem`.
+pub struct MemFileOps(Arc<Process>);
+
+impl MemFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MemFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        return_errno_with_message!(Errno::EIO, "the memory can only be read at an offset");
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(MemFile::open(self.0.clone())?)))
+    }
+}
+
+/// An opened `/proc/[pid]/mem` file.
+///
+/// Like `mm_access` in Linux, the access is checked with the credentials of the
+/// opener when the file is opened, so the file descriptor can be passed to other
+/// processes or kept after the opener drops its privileges.
+struct MemFile {
+    process: Arc<Process>,
+    /// The credentials of the opener.
+    opener_credentials: Credentials<ReadOp>,
+}
+
+impl MemFile {
+    fn open(process: Arc<Process>) -> Result<Self> {
+        let credentials =
+            Credentials::new_from(&current_thread!().as_posix_thread().unwrap().credentials());
+        if !Arc::ptr_eq(&current!(), &process) {
+            check_ptrace_access(&process, &credentials)?;
+        }
+
+        Ok(Self {
+            process,
+            opener_credentials: credentials,
+        })
+    }
+}
+
+impl Pollable for MemFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for MemFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the memory can only be read at an offset");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "the memory cannot be written");
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        if Arc::ptr_eq(&current!(), &self.process) {
+            // The non-dumpable mappings are accessible to the process itself.
+            let current_task = Task::current().unwrap();
+            let user_space = CurrentUserSpace::new(&current_task);
+            let mut reader = user_space.reader(offset, writer.avail())?;
+            return match writer.write_fallible(&mut reader) {
+                Ok(len) => Ok(len),
+                Err((_, copied_len)) if copied_len > 0 => Ok(copied_len),
+                Err((err, _)) => Err(err.into()),
+            };
+        }
+
+        // The file may have been inherited or passed to another process, and the process
+        // may have become non-dumpable or changed its credentials (e.g., by executing a
+        // set-user-ID program) since the file was opened. Linux stops the access by
+        // binding the file to the old address space, while we check it again with the
+        // credentials of the opener.
+        check_ptrace_access(&self.process, &self.opener_credentials)?;
+
+        // The process may exit at any time, after which its VMAR is dropped.
+        let vmar = self.process.lock_root_vmar();
+        match vmar.as_ref() {
+            Some(vmar) => vmar.read_remote(offset, writer),
+            None => Ok(0),
+        }
+    }
+
+    fn write_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "the memory cannot be written");
+    }
+}
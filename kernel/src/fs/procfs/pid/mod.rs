@@ -1,7 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_rights::ReadOp;
+
 use self::{
-    cmdline::CmdlineFileOps, comm::CommFileOps, exe::ExeSymOps, fd::FdDirOps, task::TaskDirOps,
+    cmdline::CmdlineFileOps, comm::CommFileOps, cwd::CwdSymOps, environ::EnvironFileOps,
+    exe::ExeSymOps, fd::FdDirOps, fdinfo::FdInfoDirOps, maps::MapsFileOps, mem::MemFileOps,
+    root::RootSymOps, stack::StackFileOps, syscall::SyscallFileOps,
+    syscall_deny::SyscallDenyFileOps, task::TaskDirOps, wchan::WchanFileOps,
 };
 use super::template::{DirOps, ProcDir, ProcDirBuilder};
 use crate::{
@@ -11,16 +16,28 @@ use crate::{
         utils::{DirEntryVecExt, Inode},
     },
     prelude::*,
-    process::{posix_thread::AsPosixThread, Process},
+    process::{
+        credentials::capabilities::CapSet, posix_thread::AsPosixThread, Credentials, Process,
+    },
 };
 
 mod cmdline;
 mod comm;
+mod cwd;
+mod environ;
 mod exe;
 mod fd;
+mod fdinfo;
+mod maps;
+mod mem;
+mod root;
+mod stack;
 mod stat;
 mod status;
+mod syscall;
+mod syscall_deny;
 mod task;
+mod wchan;
 
 /// Represents the inode at `/proc/[pid]`.
 pub struct PidDirOps(Arc<Process>);
@@ -52,6 +69,7 @@ impl Observer<FdEvents> for ProcDir<PidDirOps> {
         if let FdEvents::DropFileTable = events {
             let mut cached_children = self.cached_children().write();
             cached_children.remove_entry_by_name("fd");
+            cached_children.remove_entry_by_name("fdinfo");
         }
     }
 }
@@ -62,10 +80,20 @@ impl DirOps for PidDirOps {
             "exe" => ExeSymOps::new_inode(self.0.clone(), this_ptr.clone()),
             "comm" => CommFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "fd" => FdDirOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "fdinfo" => FdInfoDirOps::new_inode(self.0.clone(), this_ptr.clone()),
             "cmdline" => CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "environ" => EnvironFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "maps" => MapsFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "cwd" => CwdSymOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "root" => RootSymOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "mem" => MemFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "status" => status::StatusFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "stat" => stat::StatFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "task" => TaskDirOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "wchan" => WchanFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "syscall" => SyscallFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "stack" => StackFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "syscall_deny" => SyscallDenyFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -86,9 +114,27 @@ impl DirOps for PidDirOps {
         cached_children.put_entry_if_not_found("fd", || {
             FdDirOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("fdinfo", || {
+            FdInfoDirOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
         cached_children.put_entry_if_not_found("cmdline", || {
             CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("environ", || {
+            EnvironFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("maps", || {
+            MapsFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("cwd", || {
+            CwdSymOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("root", || {
+            RootSymOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("mem", || {
+            MemFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
         cached_children.put_entry_if_not_found("status", || {
             status::StatusFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
@@ -98,5 +144,78 @@ impl DirOps for PidDirOps {
         cached_children.put_entry_if_not_found("task", || {
             TaskDirOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("wchan", || {
+            WchanFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("syscall", || {
+            SyscallFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("stack", || {
+            StackFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("syscall_deny", || {
+            SyscallDenyFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
     }
 }
+
+/// Checks whether the current process may read the sensitive information of the
+/// process, e.g., its memory, its environment variables and its mappings.
+///
+/// A process can always read its own information. Other processes are checked by
+/// [`check_ptrace_access`] with the credentials of the current thread.
+fn check_read_access(process: &Arc<Process>) -> Result<()> {
+    if Arc::ptr_eq(&current!(), process) {
+        return Ok(());
+    }
+
+    let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+    check_ptrace_access(process, &credentials)
+}
+
+/// Checks whether a process with `credentials` may access another process as a
+/// tracer does.
+///
+/// This is the same as `ptrace_may_access` with `PTRACE_MODE_FSCREDS` in Linux.
+/// Unless the accessor has `CAP_SYS_PTRACE`,
+/// - its file system user ID must match the real, effective and saved user IDs of
+///   the process, and so must its file system group ID for the group IDs;
+/// - the process must be dumpable;
+/// - the permitted capabilities of the process must be a subset of its permitted
+///   capabilities.
+fn check_ptrace_access(process: &Process, credentials: &Credentials<ReadOp>) -> Result<()> {
+    if credentials.effective_capset().contains(CapSet::SYS_PTRACE) {
+        return Ok(());
+    }
+
+    let main_thread = process.main_thread();
+    let target_credentials = main_thread.as_posix_thread().unwrap().credentials();
+
+    let uid = credentials.fsuid();
+    let gid = credentials.fsgid();
+    if target_credentials.ruid() != uid
+        || target_credentials.euid() != uid
+        || target_credentials.suid() != uid
+        || target_credentials.rgid() != gid
+        || target_credentials.egid() != gid
+        || target_credentials.sgid() != gid
+    {
+        return_errno_with_message!(Errno::EACCES, "the process is owned by another user");
+    }
+
+    if !process.is_dumpable() {
+        return_errno_with_message!(Errno::EACCES, "the process is not dumpable");
+    }
+
+    if !credentials
+        .permitted_capset()
+        .contains(target_credentials.permitted_capset())
+    {
+        return_errno_with_message!(
+            Errno::EACCES,
+            "the process has capabilities that the accessor does not have"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::{ProcSymBuilder, SymOps},
+        utils::Inode,
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/root`.
+///
+/// The link points to the root directory of the process, which is changed by
+/// `chroot`.
+pub struct RootSymOps(Arc<Process>);
+
+impl RootSymOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcSymBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl SymOps for RootSymOps {
+    fn read_link(&self) -> Result<String> {
+        let main_thread = self.0.main_thread();
+        let fs = main_thread.as_posix_thread().unwrap().fs().clone();
+        let root = fs.resolver().read().root().abs_path();
+        Ok(root)
+    }
+
+    fn is_magic_link(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/stack`.
+///
+/// The file shows the kernel stack of the main thread if it is blocked. Since
+/// the kernel stack of another task cannot be unwound, only the frame that
+/// makes the blocking call is shown. Reading the file requires
+/// `CAP_SYS_ADMIN`, since it exposes kernel internals.
+pub struct StackFileOps(Arc<Process>);
+
+impl StackFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for StackFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+            return_errno_with_message!(
+                Errno::EACCES,
+                "reading kernel stacks requires CAP_SYS_ADMIN"
+            );
+        }
+
+        let main_task = self.0.tasks().lock().main().clone();
+
+        let mut output = String::new();
+        if let Some(location) = main_task.wait_location() {
+            writeln!(output, "[<0>] {}:{}", location.file(), location.line()).unwrap();
+        }
+        Ok(output.into_bytes())
+    }
+}
@@ -8,13 +8,23 @@ use crate::{
         utils::Inode,
     },
     prelude::*,
-    process::posix_thread::AsPosixThread,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{sig_action::SigAction, sig_mask::SigSet, sig_num::SigNum},
+        ResourceType,
+    },
+    vm::{
+        perms::VmPerms,
+        vmar::vm_mapping::{MappingName, VmMappingInfo},
+    },
     Process,
 };
 
 /// Represents the inode at `/proc/[pid]/status`.
 /// See https://github.com/torvalds/linux/blob/ce1c54fdff7c4556b08f5b875a331d8952e8b6b7/fs/proc/array.c#L148
-/// FIXME: Some fields are not implemented yet.
+/// FIXME: Some fields are not implemented yet, e.g., the memory usage in pages
+/// and the context switches.
 ///
 /// Fields:
 /// - Name:   The name of the process.
@@ -73,14 +83,49 @@ impl FileOps for StatusFileOps {
     fn data(&self) -> Result<Vec<u8>> {
         let process = &self.0;
         let main_thread = process.main_thread();
-        let file_table = main_thread.as_posix_thread().unwrap().file_table();
+        let posix_thread = main_thread.as_posix_thread().unwrap();
+        let file_table = posix_thread.file_table();
+        let credentials = posix_thread.credentials();
+        let is_zombie = process.status().is_zombie();
 
         let mut status_output = String::new();
-        writeln!(status_output, "Name:\t{}", process.executable_path()).unwrap();
+        writeln!(status_output, "Name:\t{}", comm(process)).unwrap();
+        writeln!(
+            status_output,
+            "Umask:\t{:04o}",
+            posix_thread.fs().umask().read().get()
+        )
+        .unwrap();
+        let state = if is_zombie {
+            "Z (zombie)"
+        } else {
+            "R (running)"
+        };
+        writeln!(status_output, "State:\t{}", state).unwrap();
         writeln!(status_output, "Tgid:\t{}", process.pid()).unwrap();
+        writeln!(status_output, "Ngid:\t0").unwrap();
         writeln!(status_output, "Pid:\t{}", process.pid()).unwrap();
         writeln!(status_output, "PPid:\t{}", process.parent().pid()).unwrap();
-        writeln!(status_output, "TracerPid:\t{}", process.parent().pid()).unwrap(); // Assuming TracerPid is the same as PPid
+        // The processes cannot be traced yet.
+        writeln!(status_output, "TracerPid:\t0").unwrap();
+        writeln!(
+            status_output,
+            "Uid:\t{}\t{}\t{}\t{}",
+            u32::from(credentials.ruid()),
+            u32::from(credentials.euid()),
+            u32::from(credentials.suid()),
+            u32::from(credentials.fsuid())
+        )
+        .unwrap();
+        writeln!(
+            status_output,
+            "Gid:\t{}\t{}\t{}\t{}",
+            u32::from(credentials.rgid()),
+            u32::from(credentials.egid()),
+            u32::from(credentials.sgid()),
+            u32::from(credentials.fsgid())
+        )
+        .unwrap();
         writeln!(
             status_output,
             "FDSize:\t{}",
@@ -91,12 +136,142 @@ impl FileOps for StatusFileOps {
                 .unwrap_or(0)
         )
         .unwrap();
+        write!(status_output, "Groups:\t").unwrap();
+        for gid in credentials.groups().iter() {
+            write!(status_output, "{} ", u32::from(*gid)).unwrap();
+        }
+        writeln!(status_output).unwrap();
+        // There are no PID namespaces, so the IDs are the same as the ones above.
+        writeln!(status_output, "NStgid:\t{}", process.pid()).unwrap();
+        writeln!(status_output, "NSpid:\t{}", process.pid()).unwrap();
+        writeln!(status_output, "NSpgid:\t{}", process.pgid()).unwrap();
+        writeln!(status_output, "NSsid:\t{}", process.sid()).unwrap();
+
+        // The zombie processes have no memory.
+        let mappings = process
+            .lock_root_vmar()
+            .as_ref()
+            .map(|vmar| vmar.mappings_info());
+        if let Some(mappings) = mappings {
+            write_vm_sizes(&mut status_output, &mappings);
+        }
+
         writeln!(
             status_output,
             "Threads:\t{}",
             process.tasks().lock().as_slice().len()
         )
         .unwrap();
+
+        let sig_pending = posix_thread.sig_pending();
+        writeln!(
+            status_output,
+            "SigQ:\t{}/{}",
+            sig_pending.count(),
+            process
+                .resource_limits()
+                .get_rlimit(ResourceType::RLIMIT_SIGPENDING)
+                .get_cur()
+        )
+        .unwrap();
+        writeln!(status_output, "SigPnd:\t{:016x}", u64::from(sig_pending)).unwrap();
+        writeln!(status_output, "ShdPnd:\t{:016x}", 0).unwrap();
+        let sig_mask = posix_thread
+            .sig_mask()
+            .load(core::sync::atomic::Ordering::Relaxed);
+        writeln!(status_output, "SigBlk:\t{:016x}", u64::from(sig_mask)).unwrap();
+        let (sig_ignored, sig_caught) = {
+            let sig_dispositions = process.sig_dispositions().lock();
+            let mut ignored = SigSet::new_empty();
+            let mut caught = SigSet::new_empty();
+            for sig_num in (1..=64u8).filter_map(|num| SigNum::try_from(num).ok()) {
+                match sig_dispositions.get(sig_num) {
+                    SigAction::Ign => ignored += sig_num,
+                    SigAction::User { .. } => caught += sig_num,
+                    SigAction::Dfl => {}
+                }
+            }
+            (ignored, caught)
+        };
+        writeln!(status_output, "SigIgn:\t{:016x}", u64::from(sig_ignored)).unwrap();
+        writeln!(status_output, "SigCgt:\t{:016x}", u64::from(sig_caught)).unwrap();
+
+        writeln!(
+            status_output,
+            "CapInh:\t{:016x}",
+            credentials.inheritable_capset().bits()
+        )
+        .unwrap();
+        writeln!(
+            status_output,
+            "CapPrm:\t{:016x}",
+            credentials.permitted_capset().bits()
+        )
+        .unwrap();
+        writeln!(
+            status_output,
+            "CapEff:\t{:016x}",
+            credentials.effective_capset().bits()
+        )
+        .unwrap();
+        // The bounding set and the ambient set are not supported yet.
+        writeln!(status_output, "CapBnd:\t{:016x}", CapSet::all().bits()).unwrap();
+        writeln!(status_output, "CapAmb:\t{:016x}", 0).unwrap();
+
+        let num_cpus = ostd::cpu::num_cpus();
+        writeln!(
+            status_output,
+            "Cpus_allowed:\t{:x}",
+            u64::MAX >> (u64::BITS as usize - num_cpus.min(64))
+        )
+        .unwrap();
+        writeln!(status_output, "Cpus_allowed_list:\t0-{}", num_cpus - 1).unwrap();
         Ok(status_output.into_bytes())
     }
 }
+
+/// Returns the name of the process, which is the same as `/proc/[pid]/comm`.
+fn comm(process: &Process) -> String {
+    const TASK_COMM_LEN: usize = 16;
+
+    let exe_path = process.executable_path();
+    let mut comm = exe_path.rsplit('/').next().unwrap_or(&exe_path).to_string();
+    if comm.len() >= TASK_COMM_LEN {
+        let mut len = TASK_COMM_LEN - 1;
+        while !comm.is_char_boundary(len) {
+            len -= 1;
+        }
+        comm.truncate(len);
+    }
+    comm
+}
+
+/// Writes the sizes of the virtual memory, which are summed up from the mappings.
+fn write_vm_sizes(output: &mut String, mappings: &[VmMappingInfo]) {
+    let size_in_kb = |filter: &dyn Fn(&VmMappingInfo) -> bool| -> usize {
+        mappings
+            .iter()
+            .filter(|mapping| filter(mapping))
+            .map(|mapping| mapping.range.len())
+            .sum::<usize>()
+            / 1024
+    };
+
+    let vm_size = size_in_kb(&|mapping| !mapping.perms.is_empty());
+    let vm_data = size_in_kb(&|mapping| {
+        mapping.perms.contains(VmPerms::WRITE)
+            && !mapping.is_shared
+            && !matches!(mapping.name, Some(MappingName::Stack))
+    });
+    let vm_stack = size_in_kb(&|mapping| matches!(mapping.name, Some(MappingName::Stack)));
+    let vm_exe = size_in_kb(&|mapping| {
+        mapping.perms.contains(VmPerms::EXEC)
+            && !mapping.perms.contains(VmPerms::WRITE)
+            && matches!(mapping.name, Some(MappingName::File(_)))
+    });
+
+    writeln!(output, "VmSize:\t{:8} kB", vm_size).unwrap();
+    writeln!(output, "VmData:\t{:8} kB", vm_data).unwrap();
+    writeln!(output, "VmStk:\t{:8} kB", vm_stack).unwrap();
+    writeln!(output, "VmExe:\t{:8} kB", vm_exe).unwrap();
+}
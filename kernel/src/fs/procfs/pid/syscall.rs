@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/syscall`.
+///
+/// The file shows the system call that the main thread is blocked in:
+/// - `running` if the thread is not blocked;
+/// - `<num> <args>... <sp> <pc>` if the thread is blocked in a system call;
+/// - `-1 <sp> <pc>` if the thread is blocked outside any system call.
+///
+/// Reference: <https://man7.org/linux/man-pages/man5/proc_pid_syscall.5.html>
+pub struct SyscallFileOps(Arc<Process>);
+
+impl SyscallFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SyscallFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let main_task = self.0.tasks().lock().main().clone();
+        if main_task.wait_location().is_none() {
+            return Ok(b"running\n".to_vec());
+        }
+
+        let posix_thread = main_task.as_posix_thread().unwrap();
+        let record = posix_thread.syscall_info().read();
+
+        let mut output = String::new();
+        match record.syscall {
+            Some((num, args)) => {
+                write!(output, "{}", num).unwrap();
+                for arg in args {
+                    write!(output, " 0x{:x}", arg).unwrap();
+                }
+            }
+            None => output.push_str("-1"),
+        }
+        writeln!(
+            output,
+            " 0x{:x} 0x{:x}",
+            record.stack_pointer, record.instruction_pointer
+        )
+        .unwrap();
+
+        Ok(output.into_bytes())
+    }
+}
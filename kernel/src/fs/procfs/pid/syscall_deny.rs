@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::{
+            sys::update_syscall_list,
+            template::{FileOps, ProcFileBuilder},
+        },
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/syscall_deny`.
+///
+/// The file lists the syscalls denied for the process, in addition to those denied for all
+/// processes. Writing the file requires `CAP_SYS_ADMIN`, so a privileged supervisor can
+/// restrict a process that it does not trust.
+pub struct SyscallDenyFileOps(Arc<Process>);
+
+impl SyscallDenyFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o644))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SyscallDenyFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.0.syscall_deny_list().format().into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        update_syscall_list(self.0.syscall_deny_list(), reader)
+    }
+}
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/wchan`.
+///
+/// Linux reports the name of the kernel function where the main thread is
+/// blocked. We report the source location of the blocking call instead, or
+/// `0` if the thread is not blocked.
+pub struct WchanFileOps(Arc<Process>);
+
+impl WchanFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for WchanFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let main_task = self.0.tasks().lock().main().clone();
+
+        // Like Linux, there is no trailing newline.
+        let output = match main_task.wait_location() {
+            Some(location) => format!("{}:{}", location.file(), location.line()),
+            None => String::from("0"),
+        };
+        Ok(output.into_bytes())
+    }
+}
@@ -1,18 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub(in crate::fs::procfs) use self::syscall_list::update_syscall_list;
 use crate::{
     fs::{
         procfs::{
-            sys::kernel::cap_last_cap::CapLastCapFileOps,
+            sys::kernel::{cap_last_cap::CapLastCapFileOps, syscall_list::SyscallListFileOps},
             template::{DirOps, ProcDirBuilder},
             ProcDir,
         },
         utils::{DirEntryVecExt, Inode},
     },
     prelude::*,
+    syscall::audit,
 };
 
 mod cap_last_cap;
+mod syscall_list;
 
 /// Represents the inode at `/proc/sys/kernel`.
 pub struct KernelDirOps;
@@ -27,6 +30,12 @@ impl DirOps for KernelDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let inode = match name {
             "cap_last_cap" => CapLastCapFileOps::new_inode(this_ptr.clone()),
+            "syscall_deny" => {
+                SyscallListFileOps::new_inode(audit::global_deny_list(), this_ptr.clone())
+            }
+            "syscall_log" => {
+                SyscallListFileOps::new_inode(audit::global_log_list(), this_ptr.clone())
+            }
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -41,5 +50,11 @@ impl DirOps for KernelDirOps {
         cached_children.put_entry_if_not_found("cap_last_cap", || {
             CapLastCapFileOps::new_inode(this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("syscall_deny", || {
+            SyscallListFileOps::new_inode(audit::global_deny_list(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("syscall_log", || {
+            SyscallListFileOps::new_inode(audit::global_log_list(), this_ptr.clone())
+        });
     }
 }
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    syscall::audit::SyscallSet,
+};
+
+/// Represents the inodes at `/proc/sys/kernel/syscall_deny` and `/proc/sys/kernel/syscall_log`.
+///
+/// Reading the file shows the syscall numbers in the list. Writing the file updates the list
+/// (see [`SyscallSet::update`]), which requires `CAP_SYS_ADMIN`.
+pub struct SyscallListFileOps(&'static SyscallSet);
+
+impl SyscallListFileOps {
+    pub fn new_inode(list: &'static SyscallSet, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(list))
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o644))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SyscallListFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.0.format().into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        update_syscall_list(self.0, reader)
+    }
+}
+
+/// Updates a syscall list with the command written by the current thread.
+pub(in crate::fs::procfs) fn update_syscall_list(
+    list: &SyscallSet,
+    reader: &mut VmReader,
+) -> Result<usize> {
+    let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+    if !credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "updating syscall lists requires CAP_SYS_ADMIN"
+        );
+    }
+
+    let buf = reader.collect()?;
+    let command = core::str::from_utf8(&buf)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the command is not valid UTF-8"))?;
+    list.update(command)?;
+
+    Ok(buf.len())
+}
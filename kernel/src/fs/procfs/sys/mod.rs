@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use self::kernel::KernelDirOps;
+pub(super) use self::kernel::update_syscall_list;
+use self::{kernel::KernelDirOps, net::NetDirOps};
 use crate::{
     fs::{
         procfs::template::{DirOps, ProcDir, ProcDirBuilder},
@@ -10,6 +11,7 @@ use crate::{
 };
 
 mod kernel;
+mod net;
 
 /// Represents the inode at `/proc/sys`.
 pub struct SysDirOps;
@@ -24,6 +26,7 @@ impl DirOps for SysDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let inode = match name {
             "kernel" => KernelDirOps::new_inode(this_ptr.clone()),
+            "net" => NetDirOps::new_inode(this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -36,6 +39,7 @@ impl DirOps for SysDirOps {
         };
         let mut cached_children = this.cached_children().write();
         cached_children
-            .put_entry_if_not_found("kernel", || KernelDirOps::new_inode(this_ptr.clone()))
+            .put_entry_if_not_found("kernel", || KernelDirOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("net", || NetDirOps::new_inode(this_ptr.clone()));
     }
 }
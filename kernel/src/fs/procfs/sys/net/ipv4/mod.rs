@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use self::{ping_group_range::PingGroupRangeFileOps, tcp_syncookies::TcpSynCookiesFileOps};
+use crate::{
+    fs::{
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+mod ping_group_range;
+mod tcp_syncookies;
+
+/// Represents the inode at `/proc/sys/net/ipv4`.
+pub struct Ipv4DirOps;
+
+impl Ipv4DirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for Ipv4DirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "ping_group_range" => PingGroupRangeFileOps::new_inode(this_ptr.clone()),
+            "tcp_syncookies" => TcpSynCookiesFileOps::new_inode(this_ptr.clone()),
+            _ => return_errno!(Errno::ENOENT),
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<Ipv4DirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("ping_group_range", || {
+            PingGroupRangeFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("tcp_syncookies", || {
+            TcpSynCookiesFileOps::new_inode(this_ptr.clone())
+        });
+    }
+}
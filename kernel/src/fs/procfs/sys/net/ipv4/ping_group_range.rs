@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    net::socket::ip::datagram::{ping_group_range, set_ping_group_range},
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// Represents the inode at `/proc/sys/net/ipv4/ping_group_range`.
+///
+/// Reading the file shows the range of the groups that are allowed to create ping sockets.
+/// Writing the file updates the range, which requires `CAP_NET_ADMIN`.
+pub struct PingGroupRangeFileOps;
+
+impl PingGroupRangeFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self)
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o644))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PingGroupRangeFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let (lo, hi) = ping_group_range();
+        let output = format!("{}\t{}\n", u32::from(lo), u32::from(hi));
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "updating the ping group range requires CAP_NET_ADMIN"
+            );
+        }
+
+        let buf = reader.collect()?;
+        let (lo, hi) = core::str::from_utf8(&buf)
+            .ok()
+            .and_then(|range| {
+                let mut gids = range.split_whitespace().map(str::parse::<u32>);
+                match (gids.next(), gids.next(), gids.next()) {
+                    (Some(Ok(lo)), Some(Ok(hi)), None) => Some((lo, hi)),
+                    _ => None,
+                }
+            })
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the ping group range is invalid"))?;
+        set_ping_group_range(lo, hi)?;
+
+        Ok(buf.len())
+    }
+}
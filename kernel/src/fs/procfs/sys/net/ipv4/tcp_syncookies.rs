@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_bigtcp::socket::{set_syn_cookies, syn_cookies, SynCookies};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// Represents the inode at `/proc/sys/net/ipv4/tcp_syncookies`.
+///
+/// Reading the file shows when SYN cookies are sent: `0` (never), `1` (when the SYN queue
+/// overflows), or `2` (always). Writing the file updates the setting, which requires
+/// `CAP_NET_ADMIN`.
+pub struct TcpSynCookiesFileOps;
+
+impl TcpSynCookiesFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self)
+            .parent(parent)
+            .mode(InodeMode::from_bits_truncate(0o644))
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for TcpSynCookiesFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = format!("{}\n", syn_cookies() as u8);
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "updating the SYN cookie setting requires CAP_NET_ADMIN"
+            );
+        }
+
+        let buf = reader.collect()?;
+        let mode = core::str::from_utf8(&buf)
+            .ok()
+            .and_then(|value| value.trim().parse::<u8>().ok())
+            .and_then(|value| SynCookies::try_from(value).ok())
+            .ok_or_else(|| {
+                Error::with_message(Errno::EINVAL, "the SYN cookie setting is invalid")
+            })?;
+        set_syn_cookies(mode);
+
+        Ok(buf.len())
+    }
+}
@@ -87,6 +87,10 @@ impl<S: SymOps + 'static> Inode for ProcSym<S> {
         Err(Error::new(Errno::EPERM))
     }
 
+    fn is_magic_link(&self) -> bool {
+        self.inner.is_magic_link()
+    }
+
     fn is_dentry_cacheable(&self) -> bool {
         !self.common.is_volatile()
     }
@@ -94,4 +98,9 @@ impl<S: SymOps + 'static> Inode for ProcSym<S> {
 
 pub trait SymOps: Sync + Send {
     fn read_link(&self) -> Result<String>;
+
+    /// Returns whether the symlink is a magic link, see [`Inode::is_magic_link`].
+    fn is_magic_link(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,606 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Disk quotas.
+//!
+//! A file system that supports quotas charges the space and the inodes of its files to the user,
+//! the group, and the project that own them. The charges are checked against the limits of the
+//! owners, and the allocations fail with `EDQUOT` if the limits are exceeded.
+//!
+//! The limits and the usage are persistent in the quota files, which are regular files in the
+//! file system itself and are enabled with `quotactl(Q_QUOTAON)`. The quota files are never
+//! charged.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use self::tree::QuotaTree;
+use super::utils::Inode;
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    time::clocks::RealTimeCoarseClock,
+};
+
+mod tree;
+
+pub use tree::QFMT_VFS_V1;
+
+/// The number of quota types.
+const NR_QUOTA_TYPES: usize = 3;
+
+/// The type of a quota, which determines the owners to charge.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromInt)]
+pub enum QuotaType {
+    User = 0,
+    Group = 1,
+    Project = 2,
+}
+
+impl QuotaType {
+    const ALL: [Self; NR_QUOTA_TYPES] = [Self::User, Self::Group, Self::Project];
+}
+
+/// The owners that the space and the inode of a file are charged to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaIds {
+    pub uid: u32,
+    pub gid: u32,
+    pub project_id: u32,
+}
+
+impl QuotaIds {
+    fn id(&self, type_: QuotaType) -> u32 {
+        match type_ {
+            QuotaType::User => self.uid,
+            QuotaType::Group => self.gid,
+            QuotaType::Project => self.project_id,
+        }
+    }
+}
+
+/// The limits and the usage of an owner.
+///
+/// A limit of zero means no limit. The space is in bytes, and the times are the seconds since
+/// the epoch when the grace periods of the soft limits end.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dquot {
+    pub bhardlimit: u64,
+    pub bsoftlimit: u64,
+    pub curspace: u64,
+    pub ihardlimit: u64,
+    pub isoftlimit: u64,
+    pub curinodes: u64,
+    pub btime: u64,
+    pub itime: u64,
+}
+
+bitflags! {
+    /// The fields of a [`Dquot`] to set.
+    ///
+    /// The values are the same as the `QIF_*` flags of Linux.
+    pub struct DquotFields: u32 {
+        const BLIMITS = 1 << 0;
+        const SPACE = 1 << 1;
+        const ILIMITS = 1 << 2;
+        const INODES = 1 << 3;
+        const BTIME = 1 << 4;
+        const ITIME = 1 << 5;
+    }
+}
+
+impl Dquot {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn check_alloc(&self, space: u64, inodes: u64, now: u64) -> Result<()> {
+        if space > 0 {
+            let new_space = self.curspace.saturating_add(space);
+            if self.bhardlimit != 0 && new_space > self.bhardlimit {
+                return_errno_with_message!(Errno::EDQUOT, "the space hard limit is exceeded");
+            }
+            if self.bsoftlimit != 0
+                && new_space > self.bsoftlimit
+                && self.btime != 0
+                && now >= self.btime
+            {
+                return_errno_with_message!(Errno::EDQUOT, "the space grace period is over");
+            }
+        }
+
+        if inodes > 0 {
+            let new_inodes = self.curinodes.saturating_add(inodes);
+            if self.ihardlimit != 0 && new_inodes > self.ihardlimit {
+                return_errno_with_message!(Errno::EDQUOT, "the inode hard limit is exceeded");
+            }
+            if self.isoftlimit != 0
+                && new_inodes > self.isoftlimit
+                && self.itime != 0
+                && now >= self.itime
+            {
+                return_errno_with_message!(Errno::EDQUOT, "the inode grace period is over");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn alloc(&mut self, space: u64, inodes: u64, now: u64, grace_times: (u64, u64)) {
+        self.curspace = self.curspace.saturating_add(space);
+        self.curinodes = self.curinodes.saturating_add(inodes);
+
+        // The grace periods start once the soft limits are exceeded.
+        if self.bsoftlimit != 0 && self.curspace > self.bsoftlimit && self.btime == 0 {
+            self.btime = now + grace_times.0;
+        }
+        if self.isoftlimit != 0 && self.curinodes > self.isoftlimit && self.itime == 0 {
+            self.itime = now + grace_times.1;
+        }
+    }
+
+    fn free(&mut self, space: u64, inodes: u64) {
+        self.curspace = self.curspace.saturating_sub(space);
+        self.curinodes = self.curinodes.saturating_sub(inodes);
+
+        if self.curspace <= self.bsoftlimit {
+            self.btime = 0;
+        }
+        if self.curinodes <= self.isoftlimit {
+            self.itime = 0;
+        }
+    }
+
+    fn set(&mut self, new: &Dquot, fields: DquotFields, now: u64, grace_times: (u64, u64)) {
+        if fields.contains(DquotFields::BLIMITS) {
+            self.bhardlimit = new.bhardlimit;
+            self.bsoftlimit = new.bsoftlimit;
+        }
+        if fields.contains(DquotFields::SPACE) {
+            self.curspace = new.curspace;
+        }
+        if fields.contains(DquotFields::ILIMITS) {
+            self.ihardlimit = new.ihardlimit;
+            self.isoftlimit = new.isoftlimit;
+        }
+        if fields.contains(DquotFields::INODES) {
+            self.curinodes = new.curinodes;
+        }
+        if fields.contains(DquotFields::BTIME) {
+            self.btime = new.btime;
+        }
+        if fields.contains(DquotFields::ITIME) {
+            self.itime = new.itime;
+        }
+
+        // Like Linux, the grace periods are restarted if the times are not set explicitly.
+        if self.bsoftlimit == 0 || self.curspace <= self.bsoftlimit {
+            self.btime = 0;
+        } else if !fields.contains(DquotFields::BTIME) {
+            self.btime = now + grace_times.0;
+        }
+        if self.isoftlimit == 0 || self.curinodes <= self.isoftlimit {
+            self.itime = 0;
+        } else if !fields.contains(DquotFields::ITIME) {
+            self.itime = now + grace_times.1;
+        }
+    }
+}
+
+/// The information of a quota type.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuotaInfo {
+    /// The grace period of the space soft limits in seconds.
+    pub bgrace: u64,
+    /// The grace period of the inode soft limits in seconds.
+    pub igrace: u64,
+    pub flags: u32,
+}
+
+bitflags! {
+    /// The fields of a [`QuotaInfo`] to set.
+    ///
+    /// The values are the same as the `IIF_*` flags of Linux.
+    pub struct QuotaInfoFields: u32 {
+        const BGRACE = 1 << 0;
+        const IGRACE = 1 << 1;
+        const FLAGS = 1 << 2;
+    }
+}
+
+/// The quotas of a file system.
+pub struct Quota {
+    states: Mutex<[Option<QuotaState>; NR_QUOTA_TYPES]>,
+    /// The inode numbers of the quota files, or zero if the quota types are disabled.
+    ///
+    /// They are checked without locking `states`, since the quota files are written while
+    /// `states` is locked.
+    file_inos: [AtomicU64; NR_QUOTA_TYPES],
+}
+
+/// The state of an enabled quota type.
+struct QuotaState {
+    tree: QuotaTree,
+    dquots: BTreeMap<u32, CachedDquot>,
+}
+
+struct CachedDquot {
+    dquot: Dquot,
+    /// The offset of the entry in the quota file, or `None` if there is no entry yet.
+    offset: Option<usize>,
+    is_dirty: bool,
+}
+
+impl QuotaState {
+    fn dquot(&mut self, id: u32) -> Result<&mut CachedDquot> {
+        if !self.dquots.contains_key(&id) {
+            let cached_dquot = match self.tree.read_dquot(id)? {
+                Some((offset, dquot)) => CachedDquot {
+                    dquot,
+                    offset: Some(offset),
+                    is_dirty: false,
+                },
+                None => CachedDquot {
+                    dquot: Dquot::default(),
+                    offset: None,
+                    is_dirty: false,
+                },
+            };
+            self.dquots.insert(id, cached_dquot);
+        }
+
+        Ok(self.dquots.get_mut(&id).unwrap())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        for (id, cached_dquot) in self.dquots.iter_mut() {
+            if !cached_dquot.is_dirty {
+                continue;
+            }
+            let offset = self
+                .tree
+                .write_dquot(*id, cached_dquot.offset, &cached_dquot.dquot)?;
+            cached_dquot.offset = Some(offset);
+            cached_dquot.is_dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl Quota {
+    pub fn new() -> Self {
+        Self {
+            states: Mutex::new([const { None }; NR_QUOTA_TYPES]),
+            file_inos: [const { AtomicU64::new(0) }; NR_QUOTA_TYPES],
+        }
+    }
+
+    /// Enables the quota type with the quota file.
+    pub fn quota_on(&self, type_: QuotaType, format: u32, file: Arc<dyn Inode>) -> Result<()> {
+        if format != QFMT_VFS_V1 {
+            return_errno_with_message!(Errno::ESRCH, "the quota format is not supported");
+        }
+
+        let mut states = self.states.lock();
+        let state = &mut states[type_ as usize];
+        if state.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the quota type is already enabled");
+        }
+
+        let file_ino = file.ino();
+        let tree = QuotaTree::open(file, type_)?;
+        *state = Some(QuotaState {
+            tree,
+            dquots: BTreeMap::new(),
+        });
+        self.file_inos[type_ as usize].store(file_ino, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Disables the quota type after writing back the quotas.
+    ///
+    /// Nothing happens if the quota type is not enabled.
+    pub fn quota_off(&self, type_: QuotaType) -> Result<()> {
+        let mut states = self.states.lock();
+        let Some(state) = states[type_ as usize].as_mut() else {
+            return Ok(());
+        };
+
+        state.sync()?;
+        states[type_ as usize] = None;
+        self.file_inos[type_ as usize].store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the format of the quota type.
+    pub fn format(&self, type_: QuotaType) -> Result<u32> {
+        let states = self.states.lock();
+        if states[type_ as usize].is_none() {
+            return_errno_with_message!(Errno::ESRCH, "the quota type is not enabled");
+        }
+        Ok(QFMT_VFS_V1)
+    }
+
+    /// Returns the information of the quota type.
+    pub fn info(&self, type_: QuotaType) -> Result<QuotaInfo> {
+        let states = self.states.lock();
+        let state = enabled_state(&states, type_)?;
+
+        let (bgrace, igrace) = state.tree.grace_times();
+        Ok(QuotaInfo {
+            bgrace,
+            igrace,
+            flags: 0,
+        })
+    }
+
+    /// Sets the fields of the information of the quota type.
+    pub fn set_info(
+        &self,
+        type_: QuotaType,
+        new_info: &QuotaInfo,
+        fields: QuotaInfoFields,
+    ) -> Result<()> {
+        if fields.contains(QuotaInfoFields::FLAGS) && new_info.flags != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the quota flags are not supported");
+        }
+
+        let mut states = self.states.lock();
+        let state = enabled_state_mut(&mut states, type_)?;
+
+        let (mut bgrace, mut igrace) = state.tree.grace_times();
+        if fields.contains(QuotaInfoFields::BGRACE) {
+            bgrace = new_info.bgrace;
+        }
+        if fields.contains(QuotaInfoFields::IGRACE) {
+            igrace = new_info.igrace;
+        }
+        state.tree.set_grace_times(bgrace, igrace)
+    }
+
+    /// Returns the quota of the ID.
+    pub fn dquot(&self, type_: QuotaType, id: u32) -> Result<Dquot> {
+        let mut states = self.states.lock();
+        let state = enabled_state_mut(&mut states, type_)?;
+        Ok(state.dquot(id)?.dquot)
+    }
+
+    /// Returns the first ID that is not less than `id` and has a nonempty quota.
+    pub fn next_dquot(&self, type_: QuotaType, id: u32) -> Result<(u32, Dquot)> {
+        let mut states = self.states.lock();
+        let state = enabled_state_mut(&mut states, type_)?;
+
+        let mut id = id;
+        loop {
+            let disk_id = state.tree.next_id(id)?;
+            let cached_id = state.dquots.range(id..).next().map(|(id, _)| *id);
+            let next_id = match (disk_id, cached_id) {
+                (Some(disk_id), Some(cached_id)) => disk_id.min(cached_id),
+                (Some(next_id), None) | (None, Some(next_id)) => next_id,
+                (None, None) => return_errno_with_message!(Errno::ENOENT, "no more quotas"),
+            };
+
+            let dquot = state.dquot(next_id)?.dquot;
+            if !dquot.is_empty() {
+                return Ok((next_id, dquot));
+            }
+            id = next_id
+                .checked_add(1)
+                .ok_or_else(|| Error::with_message(Errno::ENOENT, "no more quotas"))?;
+        }
+    }
+
+    /// Sets the fields of the quota of the ID.
+    pub fn set_dquot(
+        &self,
+        type_: QuotaType,
+        id: u32,
+        new_dquot: &Dquot,
+        fields: DquotFields,
+    ) -> Result<()> {
+        let mut states = self.states.lock();
+        let state = enabled_state_mut(&mut states, type_)?;
+
+        let grace_times = state.tree.grace_times();
+        let cached_dquot = state.dquot(id)?;
+        cached_dquot
+            .dquot
+            .set(new_dquot, fields, now(), grace_times);
+        cached_dquot.is_dirty = true;
+        Ok(())
+    }
+
+    /// Writes back the quotas of the enabled quota types.
+    pub fn sync(&self) -> Result<()> {
+        let mut states = self.states.lock();
+        for state in states.iter_mut().flatten() {
+            state.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Charges the space to the owners of the file.
+    pub fn alloc_space(&self, ino: u64, ids: &QuotaIds, space: u64) -> Result<()> {
+        self.alloc(ino, ids, space, 0)
+    }
+
+    /// Releases the space charged to the owners of the file.
+    pub fn free_space(&self, ino: u64, ids: &QuotaIds, space: u64) {
+        self.free(ino, ids, space, 0);
+    }
+
+    /// Charges a new inode to its owners.
+    pub fn alloc_inode(&self, ino: u64, ids: &QuotaIds) -> Result<()> {
+        self.alloc(ino, ids, 0, 1)
+    }
+
+    /// Releases the inode charged to its owners.
+    pub fn free_inode(&self, ino: u64, ids: &QuotaIds) {
+        self.free(ino, ids, 0, 1);
+    }
+
+    /// Transfers the space and the inode of the file from the old owners to the new ones.
+    pub fn transfer(
+        &self,
+        ino: u64,
+        old_ids: &QuotaIds,
+        new_ids: &QuotaIds,
+        space: u64,
+    ) -> Result<()> {
+        if !self.is_charged(ino) {
+            return Ok(());
+        }
+
+        let ignores_limits = ignores_limits();
+        let now = now();
+        let mut states = self.states.lock();
+
+        // Checks all the limits first, so that nothing is transferred on failure.
+        for type_ in QuotaType::ALL {
+            let Some(state) = states[type_ as usize].as_mut() else {
+                continue;
+            };
+            if old_ids.id(type_) == new_ids.id(type_) || ignores_limits {
+                continue;
+            }
+            state
+                .dquot(new_ids.id(type_))?
+                .dquot
+                .check_alloc(space, 1, now)?;
+        }
+
+        for type_ in QuotaType::ALL {
+            let Some(state) = states[type_ as usize].as_mut() else {
+                continue;
+            };
+            if old_ids.id(type_) == new_ids.id(type_) {
+                continue;
+            }
+
+            let grace_times = state.tree.grace_times();
+            let old_dquot = state.dquot(old_ids.id(type_))?;
+            old_dquot.dquot.free(space, 1);
+            old_dquot.is_dirty = true;
+            let new_dquot = state.dquot(new_ids.id(type_))?;
+            new_dquot.dquot.alloc(space, 1, now, grace_times);
+            new_dquot.is_dirty = true;
+        }
+
+        Ok(())
+    }
+
+    fn alloc(&self, ino: u64, ids: &QuotaIds, space: u64, inodes: u64) -> Result<()> {
+        if !self.is_charged(ino) {
+            return Ok(());
+        }
+
+        let ignores_limits = ignores_limits();
+        let now = now();
+        let mut states = self.states.lock();
+
+        // Checks all the limits first, so that nothing is charged on failure.
+        if !ignores_limits {
+            for (type_, state) in QuotaType::ALL.into_iter().zip(states.iter_mut()) {
+                if let Some(state) = state {
+                    state
+                        .dquot(ids.id(type_))?
+                        .dquot
+                        .check_alloc(space, inodes, now)?;
+                }
+            }
+        }
+
+        for (type_, state) in QuotaType::ALL.into_iter().zip(states.iter_mut()) {
+            if let Some(state) = state {
+                let grace_times = state.tree.grace_times();
+                let cached_dquot = state.dquot(ids.id(type_))?;
+                cached_dquot.dquot.alloc(space, inodes, now, grace_times);
+                cached_dquot.is_dirty = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn free(&self, ino: u64, ids: &QuotaIds, space: u64, inodes: u64) {
+        if !self.is_charged(ino) {
+            return;
+        }
+
+        let mut states = self.states.lock();
+        for (type_, state) in QuotaType::ALL.into_iter().zip(states.iter_mut()) {
+            let Some(state) = state else {
+                continue;
+            };
+            match state.dquot(ids.id(type_)) {
+                Ok(cached_dquot) => {
+                    cached_dquot.dquot.free(space, inodes);
+                    cached_dquot.is_dirty = true;
+                }
+                Err(err) => warn!("failed to release the quota: {:?}", err),
+            }
+        }
+    }
+
+    /// Returns whether the file is charged, i.e., whether some quota types are enabled and the
+    /// file is not a quota file.
+    fn is_charged(&self, ino: u64) -> bool {
+        let mut is_enabled = false;
+        for file_ino in self.file_inos.iter() {
+            match file_ino.load(Ordering::Relaxed) {
+                0 => (),
+                file_ino if file_ino == ino => return false,
+                _ => is_enabled = true,
+            }
+        }
+        is_enabled
+    }
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Quota {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Quota")
+            .field("file_inos", &self.file_inos)
+            .finish_non_exhaustive()
+    }
+}
+
+fn enabled_state(
+    states: &[Option<QuotaState>; NR_QUOTA_TYPES],
+    type_: QuotaType,
+) -> Result<&QuotaState> {
+    states[type_ as usize]
+        .as_ref()
+        .ok_or_else(|| Error::with_message(Errno::ESRCH, "the quota type is not enabled"))
+}
+
+fn enabled_state_mut(
+    states: &mut [Option<QuotaState>; NR_QUOTA_TYPES],
+    type_: QuotaType,
+) -> Result<&mut QuotaState> {
+    states[type_ as usize]
+        .as_mut()
+        .ok_or_else(|| Error::with_message(Errno::ESRCH, "the quota type is not enabled"))
+}
+
+/// Returns whether the current thread can exceed the limits.
+///
+/// Like Linux, the limits are not enforced with `CAP_SYS_RESOURCE`.
+fn ignores_limits() -> bool {
+    let Some(posix_thread) = current_thread!().as_posix_thread() else {
+        return true;
+    };
+    posix_thread
+        .credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE)
+}
+
+fn now() -> u64 {
+    RealTimeCoarseClock::get().read_time().as_secs()
+}
@@ -0,0 +1,462 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The quota file format of Linux (i.e., `QFMT_VFS_V1`).
+//!
+//! A quota file is divided into 1024-byte blocks. The first block holds the header and the
+//! information of the quota type. The second block is the root of a radix tree of four levels,
+//! which is indexed by the bytes of an ID from the most significant one. The leaves of the tree
+//! point to the data blocks, each of which stores several quota entries.
+//!
+//! The data blocks that have free entries are linked in a list, and so are the blocks that are
+//! completely free. The entries are never removed here, so the latter list is only consumed.
+
+use super::{Dquot, QuotaType};
+use crate::{
+    fs::utils::{Inode, InodeType},
+    prelude::*,
+};
+
+/// The ID of the quota format.
+pub(super) const QFMT_VFS_V1: u32 = 4;
+
+/// The size of the blocks in the quota file.
+const QUOTA_BLOCK_SIZE: usize = 1024;
+/// The number of levels of the radix tree.
+const TREE_DEPTH: usize = 4;
+/// The block number of the root of the radix tree.
+const TREE_ROOT_BLK: u32 = 1;
+/// The number of references in a tree block.
+const REFS_PER_BLOCK: usize = QUOTA_BLOCK_SIZE / size_of::<u32>();
+/// The number of entries in a data block.
+const ENTRIES_PER_BLOCK: usize =
+    (QUOTA_BLOCK_SIZE - size_of::<DataBlockHeader>()) / size_of::<DiskDquot>();
+
+/// The version of the quota format, whose entries have 64-bit fields.
+const QUOTA_VERSION: u32 = 1;
+/// The offset of the quota information in the file.
+const INFO_OFFSET: usize = size_of::<DiskHeader>();
+
+/// The size unit of the block limits stored on disk.
+const DISK_LIMIT_UNIT: u64 = 1024;
+
+/// A quota file of the `QFMT_VFS_V1` format.
+pub(super) struct QuotaTree {
+    file: Arc<dyn Inode>,
+    info: DiskInfo,
+}
+
+impl QuotaTree {
+    /// Opens a quota file of the quota type.
+    ///
+    /// The file must be initialized with a valid header, e.g., by `quotacheck`.
+    pub(super) fn open(file: Arc<dyn Inode>, type_: QuotaType) -> Result<Self> {
+        if file.type_() != InodeType::File {
+            return_errno_with_message!(Errno::EACCES, "the quota file is not a regular file");
+        }
+
+        let mut buf = [0u8; INFO_OFFSET + size_of::<DiskInfo>()];
+        let len = file.read_bytes_at(0, &mut buf)?;
+        if len < buf.len() {
+            return_errno_with_message!(Errno::EINVAL, "the quota file is too short");
+        }
+
+        let header = DiskHeader::from_bytes(&buf[..INFO_OFFSET]);
+        if header.magic != disk_magic(type_) || header.version != QUOTA_VERSION {
+            return_errno_with_message!(Errno::EINVAL, "the quota file has an invalid header");
+        }
+
+        let info = DiskInfo::from_bytes(&buf[INFO_OFFSET..]);
+        if info.blocks <= TREE_ROOT_BLK {
+            return_errno_with_message!(Errno::EINVAL, "the quota file has no radix tree");
+        }
+
+        Ok(Self { file, info })
+    }
+
+    /// Returns the grace periods of the space and inode soft limits in seconds.
+    pub(super) fn grace_times(&self) -> (u64, u64) {
+        (self.info.bgrace as u64, self.info.igrace as u64)
+    }
+
+    /// Sets the grace periods of the space and inode soft limits in seconds.
+    pub(super) fn set_grace_times(&mut self, bgrace: u64, igrace: u64) -> Result<()> {
+        let (Ok(bgrace), Ok(igrace)) = (u32::try_from(bgrace), u32::try_from(igrace)) else {
+            return_errno_with_message!(Errno::ERANGE, "the grace period is too long");
+        };
+
+        self.info.bgrace = bgrace;
+        self.info.igrace = igrace;
+        self.write_info()
+    }
+
+    /// Reads the entry of the ID.
+    ///
+    /// Returns the entry along with its offset in the file, or `None` if the ID has no entry.
+    pub(super) fn read_dquot(&self, id: u32) -> Result<Option<(usize, Dquot)>> {
+        let mut blk = TREE_ROOT_BLK;
+        for depth in 0..TREE_DEPTH {
+            blk = self.read_block(blk)?.tree_ref(tree_index(id, depth));
+            if blk == 0 {
+                return Ok(None);
+            }
+        }
+
+        let block = self.read_block(blk)?;
+        let found = (0..ENTRIES_PER_BLOCK).find_map(|i| {
+            let disk_dquot = block.entry(i);
+            (!disk_dquot.is_unused() && disk_dquot.id == id).then(|| (i, disk_dquot))
+        });
+        let Some((i, disk_dquot)) = found else {
+            return_errno_with_message!(Errno::EIO, "the quota entry is missing");
+        };
+
+        Ok(Some((entry_offset(blk, i), Dquot::from(&disk_dquot))))
+    }
+
+    /// Writes the entry of the ID.
+    ///
+    /// If `offset` is `None`, a new entry is inserted into the tree. Returns the offset of the
+    /// entry in the file.
+    pub(super) fn write_dquot(
+        &mut self,
+        id: u32,
+        offset: Option<usize>,
+        dquot: &Dquot,
+    ) -> Result<usize> {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => self.insert_dquot(id)?,
+        };
+
+        let disk_dquot = DiskDquot::new(id, dquot);
+        self.file.write_bytes_at(offset, disk_dquot.as_bytes())?;
+        Ok(offset)
+    }
+
+    /// Returns the first ID that is not less than `id` and has an entry.
+    pub(super) fn next_id(&self, id: u32) -> Result<Option<u32>> {
+        self.find_next_id(TREE_ROOT_BLK, id, 0)
+    }
+
+    fn find_next_id(&self, blk: u32, id: u32, depth: usize) -> Result<Option<u32>> {
+        let block = self.read_block(blk)?;
+        let shift = (TREE_DEPTH - 1 - depth) * 8;
+        let start = tree_index(id, depth);
+        for index in start..REFS_PER_BLOCK {
+            let child_blk = block.tree_ref(index);
+            if child_blk == 0 {
+                continue;
+            }
+
+            // Beyond the first reference, the search starts from the smallest ID of the subtree.
+            let child_id = if index == start {
+                id
+            } else {
+                let prefix = ((id as u64) >> (shift + 8) << (shift + 8)) as u32;
+                prefix | ((index as u32) << shift)
+            };
+            if depth == TREE_DEPTH - 1 {
+                return Ok(Some(child_id));
+            }
+            if let Some(next_id) = self.find_next_id(child_blk, child_id, depth + 1)? {
+                return Ok(Some(next_id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Inserts an entry of the ID and returns its offset.
+    fn insert_dquot(&mut self, id: u32) -> Result<usize> {
+        let mut blk = TREE_ROOT_BLK;
+        for depth in 0..TREE_DEPTH {
+            let mut block = self.read_block(blk)?;
+            let index = tree_index(id, depth);
+            let child_blk = block.tree_ref(index);
+
+            if child_blk != 0 {
+                if depth == TREE_DEPTH - 1 {
+                    return_errno_with_message!(Errno::EIO, "the quota entry already exists");
+                }
+                blk = child_blk;
+                continue;
+            }
+
+            let (child_blk, offset) = if depth == TREE_DEPTH - 1 {
+                let (data_blk, offset) = self.alloc_entry(id)?;
+                (data_blk, Some(offset))
+            } else {
+                let tree_blk = self.alloc_block()?;
+                self.write_block(tree_blk, &QuotaBlock::new_zeroed())?;
+                (tree_blk, None)
+            };
+            block.set_tree_ref(index, child_blk);
+            self.write_block(blk, &block)?;
+
+            if let Some(offset) = offset {
+                return Ok(offset);
+            }
+            blk = child_blk;
+        }
+
+        unreachable!("the leaves of the radix tree are always handled")
+    }
+
+    /// Allocates a free entry in a data block and marks it as used by the ID.
+    ///
+    /// Returns the block number of the data block and the offset of the entry.
+    fn alloc_entry(&mut self, id: u32) -> Result<(u32, usize)> {
+        let (blk, mut block) = if self.info.free_entry != 0 {
+            let blk = self.info.free_entry;
+            (blk, self.read_block(blk)?)
+        } else {
+            let blk = self.alloc_block()?;
+            self.info.free_entry = blk;
+            self.write_info()?;
+            (blk, QuotaBlock::new_zeroed())
+        };
+
+        let mut header = block.data_header();
+        if header.entries as usize + 1 >= ENTRIES_PER_BLOCK {
+            self.remove_free_entry_block(&mut header)?;
+        }
+        header.entries += 1;
+        block.set_data_header(&header);
+
+        let Some(i) = (0..ENTRIES_PER_BLOCK).find(|&i| block.entry(i).is_unused()) else {
+            return_errno_with_message!(Errno::EIO, "the data block of the quota file is full");
+        };
+        // Marks the entry as used. The actual content is written by the caller.
+        block.set_entry(i, &DiskDquot::new(id, &Dquot::default()));
+        self.write_block(blk, &block)?;
+
+        Ok((blk, entry_offset(blk, i)))
+    }
+
+    /// Removes a data block from the list of the blocks that have free entries.
+    fn remove_free_entry_block(&mut self, header: &mut DataBlockHeader) -> Result<()> {
+        if header.next_free != 0 {
+            let mut next_block = self.read_block(header.next_free)?;
+            let mut next_header = next_block.data_header();
+            next_header.prev_free = header.prev_free;
+            next_block.set_data_header(&next_header);
+            self.write_block(header.next_free, &next_block)?;
+        }
+        if header.prev_free != 0 {
+            let mut prev_block = self.read_block(header.prev_free)?;
+            let mut prev_header = prev_block.data_header();
+            prev_header.next_free = header.next_free;
+            prev_block.set_data_header(&prev_header);
+            self.write_block(header.prev_free, &prev_block)?;
+        } else {
+            self.info.free_entry = header.next_free;
+            self.write_info()?;
+        }
+
+        header.next_free = 0;
+        header.prev_free = 0;
+        Ok(())
+    }
+
+    /// Allocates a block, either from the list of free blocks or from the end of the file.
+    fn alloc_block(&mut self) -> Result<u32> {
+        let blk = if self.info.free_blk != 0 {
+            let blk = self.info.free_blk;
+            self.info.free_blk = self.read_block(blk)?.data_header().next_free;
+            blk
+        } else {
+            let blk = self.info.blocks;
+            self.write_block(blk, &QuotaBlock::new_zeroed())?;
+            self.info.blocks += 1;
+            blk
+        };
+
+        self.write_info()?;
+        Ok(blk)
+    }
+
+    fn read_block(&self, blk: u32) -> Result<QuotaBlock> {
+        let mut block = QuotaBlock::new_zeroed();
+        // The blocks beyond the end of the file are read as zeros.
+        self.file
+            .read_bytes_at(blk as usize * QUOTA_BLOCK_SIZE, &mut block.0)?;
+        Ok(block)
+    }
+
+    fn write_block(&self, blk: u32, block: &QuotaBlock) -> Result<()> {
+        self.file
+            .write_bytes_at(blk as usize * QUOTA_BLOCK_SIZE, &block.0)?;
+        Ok(())
+    }
+
+    fn write_info(&self) -> Result<()> {
+        self.file
+            .write_bytes_at(INFO_OFFSET, self.info.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Returns the magic number of the quota files of the quota type.
+fn disk_magic(type_: QuotaType) -> u32 {
+    match type_ {
+        QuotaType::User => 0xd9c0_1f11,
+        QuotaType::Group => 0xd9c0_1927,
+        QuotaType::Project => 0xd9c0_3f14,
+    }
+}
+
+/// Returns the index of the reference to follow in a tree block of the depth.
+fn tree_index(id: u32, depth: usize) -> usize {
+    ((id >> ((TREE_DEPTH - 1 - depth) * 8)) & 0xff) as usize
+}
+
+/// Returns the offset in the file of the `i`-th entry in the data block.
+fn entry_offset(blk: u32, i: usize) -> usize {
+    blk as usize * QUOTA_BLOCK_SIZE + size_of::<DataBlockHeader>() + i * size_of::<DiskDquot>()
+}
+
+/// A block of the quota file.
+struct QuotaBlock(Box<[u8; QUOTA_BLOCK_SIZE]>);
+
+impl QuotaBlock {
+    fn new_zeroed() -> Self {
+        Self(Box::new([0u8; QUOTA_BLOCK_SIZE]))
+    }
+
+    fn tree_ref(&self, index: usize) -> u32 {
+        let offset = index * size_of::<u32>();
+        u32::from_le_bytes(
+            self.0[offset..offset + size_of::<u32>()]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn set_tree_ref(&mut self, index: usize, blk: u32) {
+        let offset = index * size_of::<u32>();
+        self.0[offset..offset + size_of::<u32>()].copy_from_slice(&blk.to_le_bytes());
+    }
+
+    fn data_header(&self) -> DataBlockHeader {
+        DataBlockHeader::from_bytes(&self.0[..size_of::<DataBlockHeader>()])
+    }
+
+    fn set_data_header(&mut self, header: &DataBlockHeader) {
+        self.0[..size_of::<DataBlockHeader>()].copy_from_slice(header.as_bytes());
+    }
+
+    fn entry(&self, i: usize) -> DiskDquot {
+        let offset = size_of::<DataBlockHeader>() + i * size_of::<DiskDquot>();
+        DiskDquot::from_bytes(&self.0[offset..offset + size_of::<DiskDquot>()])
+    }
+
+    fn set_entry(&mut self, i: usize, disk_dquot: &DiskDquot) {
+        let offset = size_of::<DataBlockHeader>() + i * size_of::<DiskDquot>();
+        self.0[offset..offset + size_of::<DiskDquot>()].copy_from_slice(disk_dquot.as_bytes());
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DiskHeader {
+    magic: u32,
+    version: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DiskInfo {
+    /// The grace period of the space soft limit in seconds.
+    bgrace: u32,
+    /// The grace period of the inode soft limit in seconds.
+    igrace: u32,
+    flags: u32,
+    /// The number of blocks in the file.
+    blocks: u32,
+    /// The first block in the list of free blocks.
+    free_blk: u32,
+    /// The first block in the list of data blocks that have free entries.
+    free_entry: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DataBlockHeader {
+    next_free: u32,
+    prev_free: u32,
+    /// The number of used entries in the block.
+    entries: u16,
+    pad1: u16,
+    pad2: u32,
+}
+
+/// The quota entry on disk.
+///
+/// The block limits are in the unit of [`DISK_LIMIT_UNIT`], while the space usage is in bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DiskDquot {
+    id: u32,
+    pad: u32,
+    ihardlimit: u64,
+    isoftlimit: u64,
+    curinodes: u64,
+    bhardlimit: u64,
+    bsoftlimit: u64,
+    curspace: u64,
+    btime: u64,
+    itime: u64,
+}
+
+impl DiskDquot {
+    fn new(id: u32, dquot: &Dquot) -> Self {
+        let disk_dquot = Self {
+            id,
+            pad: 0,
+            ihardlimit: dquot.ihardlimit,
+            isoftlimit: dquot.isoftlimit,
+            curinodes: dquot.curinodes,
+            bhardlimit: dquot.bhardlimit.div_ceil(DISK_LIMIT_UNIT),
+            bsoftlimit: dquot.bsoftlimit.div_ceil(DISK_LIMIT_UNIT),
+            curspace: dquot.curspace,
+            btime: dquot.btime,
+            itime: dquot.itime,
+        };
+        // An entry that is all zeros is regarded as unused, so it is escaped with a nonzero
+        // `itime`.
+        if disk_dquot.is_unused() {
+            Self::escaped_empty()
+        } else {
+            disk_dquot
+        }
+    }
+
+    fn escaped_empty() -> Self {
+        let mut disk_dquot = Self::new_zeroed();
+        disk_dquot.itime = 1;
+        disk_dquot
+    }
+
+    fn is_unused(&self) -> bool {
+        self.as_bytes().iter().all(|byte| *byte == 0)
+    }
+}
+
+impl From<&DiskDquot> for Dquot {
+    fn from(disk_dquot: &DiskDquot) -> Self {
+        if disk_dquot.as_bytes() == DiskDquot::escaped_empty().as_bytes() {
+            return Self::default();
+        }
+
+        Self {
+            bhardlimit: disk_dquot.bhardlimit * DISK_LIMIT_UNIT,
+            bsoftlimit: disk_dquot.bsoftlimit * DISK_LIMIT_UNIT,
+            curspace: disk_dquot.curspace,
+            ihardlimit: disk_dquot.ihardlimit,
+            isoftlimit: disk_dquot.isoftlimit,
+            curinodes: disk_dquot.curinodes,
+            btime: disk_dquot.btime,
+            itime: disk_dquot.itime,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use core::{
+    ops::Range,
     sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
@@ -24,9 +25,10 @@ use crate::{
         named_pipe::NamedPipe,
         path::{is_dot, is_dot_or_dotdot, is_dotdot},
         utils::{
-            CStr256, CachePage, DirentVisitor, Extension, FallocMode, FileSystem, FsFlags, Inode,
-            InodeMode, InodeType, IoctlCmd, Metadata, MknodType, PageCache, PageCacheBackend,
-            Permission, SuperBlock, XattrName, XattrNamespace, XattrSetFlags,
+            CStr256, CachePage, DirentVisitor, ExtendedMetadata, Extension, FallocMode,
+            FileAttributes, FileSystem, FsFlags, Inode, InodeFlags, InodeMode, InodeType, IoctlCmd,
+            Metadata, MknodType, PageCache, PageCacheBackend, Permission, SuperBlock, XattrName,
+            XattrNamespace, XattrSetFlags,
         },
     },
     prelude::*,
@@ -83,7 +85,12 @@ impl FileSystem for RamFS {
     }
 
     fn sb(&self) -> SuperBlock {
-        self.sb.clone()
+        // The data of the file system can take up all the free memory.
+        let mut sb = self.sb.clone();
+        sb.blocks = crate::vm::mem_total() / BLOCK_SIZE;
+        sb.bfree = osdk_frame_allocator::load_total_free_size() / BLOCK_SIZE;
+        sb.bavail = sb.bfree;
+        sb
     }
 
     fn flags(&self) -> FsFlags {
@@ -190,10 +197,12 @@ struct InodeMeta {
     atime: Duration,
     mtime: Duration,
     ctime: Duration,
+    btime: Duration,
     mode: InodeMode,
     nlinks: usize,
     uid: Uid,
     gid: Gid,
+    flags: InodeFlags,
 }
 
 impl InodeMeta {
@@ -205,10 +214,12 @@ impl InodeMeta {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: now,
             mode,
             nlinks: 1,
             uid,
             gid,
+            flags: InodeFlags::empty(),
         }
     }
 
@@ -220,10 +231,12 @@ impl InodeMeta {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: now,
             mode,
             nlinks: NUM_SPECIAL_ENTRIES,
             uid,
             gid,
+            flags: InodeFlags::empty(),
         }
     }
 
@@ -544,9 +557,6 @@ impl Inode for RamInode {
             }
         };
 
-        if self.typ == InodeType::File {
-            self.set_atime(now());
-        }
         Ok(read_len)
     }
 
@@ -790,8 +800,6 @@ impl Inode for RamInode {
             .read()
             .visit_entry(offset, visitor)?;
 
-        self.set_atime(now());
-
         Ok(cnt)
     }
 
@@ -1130,6 +1138,35 @@ impl Inode for RamInode {
         }
     }
 
+    fn extended_metadata(&self) -> ExtendedMetadata {
+        let inode_metadata = self.metadata.lock();
+
+        let mut attributes = FileAttributes::empty();
+        if inode_metadata.flags.contains(InodeFlags::IMMUTABLE) {
+            attributes |= FileAttributes::IMMUTABLE;
+        }
+        if inode_metadata.flags.contains(InodeFlags::APPEND) {
+            attributes |= FileAttributes::APPEND;
+        }
+
+        ExtendedMetadata {
+            btime: Some(inode_metadata.btime),
+            attributes,
+            attributes_mask: FileAttributes::IMMUTABLE | FileAttributes::APPEND,
+        }
+    }
+
+    fn flags(&self) -> InodeFlags {
+        self.metadata.lock().flags
+    }
+
+    fn set_flags(&self, flags: InodeFlags) -> Result<()> {
+        let mut inode_metadata = self.metadata.lock();
+        inode_metadata.flags = flags;
+        inode_metadata.set_ctime(now());
+        Ok(())
+    }
+
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
         if !self.typ.is_device() {
             return (IoEvents::IN | IoEvents::OUT) & mask;
@@ -1169,9 +1206,18 @@ impl Inode for RamInode {
                 if offset >= file_size {
                     return Ok(());
                 }
-                let range = offset..file_size.min(offset + len);
-                // TODO: Think of a more light-weight approach
-                self.inner.as_file().unwrap().fill_zeros(range)
+                let end = file_size.min(offset + len);
+                let page_cache = self.inner.as_file().unwrap();
+
+                // Release the whole pages in the hole, which are zeros when they are committed
+                // again. Only the partial pages at both ends need to be filled with zeros.
+                let whole_pages = offset.align_up(BLOCK_SIZE)..end.align_down(BLOCK_SIZE);
+                if whole_pages.start >= whole_pages.end {
+                    return page_cache.fill_zeros(offset..end);
+                }
+                page_cache.fill_zeros(offset..whole_pages.start)?;
+                page_cache.fill_zeros(whole_pages.end..end)?;
+                page_cache.pages().decommit(whole_pages)
             }
             _ => {
                 return_errno_with_message!(
@@ -1182,6 +1228,26 @@ impl Inode for RamInode {
         }
     }
 
+    fn next_data_extent(&self, offset: usize) -> Result<Option<Range<usize>>> {
+        let size = self.size();
+        if offset >= size {
+            return Ok(None);
+        }
+        let Some(page_cache) = self.inner.as_file() else {
+            return Ok(Some(0..size));
+        };
+
+        // The data lives only in the page cache, so the pages that are not committed are holes.
+        let Some(pages) = page_cache.pages().next_committed_pages(offset / PAGE_SIZE) else {
+            return Ok(None);
+        };
+        let start = pages.start * PAGE_SIZE;
+        if start >= size {
+            return Ok(None);
+        }
+        Ok(Some(start..size.min(pages.end * PAGE_SIZE)))
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         if let Some(device) = self.inner.as_device() {
             return device.ioctl(cmd, arg);
@@ -1206,22 +1272,18 @@ impl Inode for RamInode {
         value_reader: &mut VmReader,
         flags: XattrSetFlags,
     ) -> Result<()> {
-        RamXattr::check_file_type_for_xattr(self.typ)?;
         self.check_permission(Permission::MAY_WRITE)?;
-        self.xattr.set(name, value_reader, flags)
+        self.xattr.set(name, value_reader, flags)?;
+        self.set_ctime(now());
+        Ok(())
     }
 
     fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
-        RamXattr::check_file_type_for_xattr(self.typ)
-            .map_err(|_| Error::with_message(Errno::ENODATA, "no available xattrs"))?;
         self.check_permission(Permission::MAY_READ)?;
         self.xattr.get(name, value_writer)
     }
 
     fn list_xattr(&self, namespace: XattrNamespace, list_writer: &mut VmWriter) -> Result<usize> {
-        if RamXattr::check_file_type_for_xattr(self.typ).is_err() {
-            return Ok(0);
-        }
         if self.check_permission(Permission::MAY_ACCESS).is_err() {
             return Ok(0);
         }
@@ -1229,9 +1291,10 @@ impl Inode for RamInode {
     }
 
     fn remove_xattr(&self, name: XattrName) -> Result<()> {
-        RamXattr::check_file_type_for_xattr(self.typ)?;
         self.check_permission(Permission::MAY_WRITE)?;
-        self.xattr.remove(name)
+        self.xattr.remove(name)?;
+        self.set_ctime(now());
+        Ok(())
     }
 }
 
@@ -7,7 +7,29 @@ pub use fs::RamFS;
 mod fs;
 mod xattr;
 
-const RAMFS_MAGIC: u64 = 0x0102_1994;
+const RAMFS_MAGIC: u64 = 0x8584_58f6;
 const BLOCK_SIZE: usize = 4096;
 const ROOT_INO: u64 = 1;
 const NAME_MAX: usize = 255;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::fs::utils::{run_benchmarks, FileSystem, FsExerciser};
+
+    #[ktest]
+    fn exerciser() {
+        crate::time::clocks::init_for_ktest();
+        let fs = RamFS::new();
+        FsExerciser::new(&fs.root_inode(), 0).run(500);
+    }
+
+    #[ktest]
+    fn benchmarks() {
+        crate::time::clocks::init_for_ktest();
+        let fs = RamFS::new();
+        run_benchmarks(&fs.root_inode());
+    }
+}
@@ -4,7 +4,7 @@ use hashbrown::{hash_map::Entry, Equivalent, HashMap};
 use spin::Once;
 
 use crate::{
-    fs::utils::{InodeType, XattrName, XattrNamespace, XattrSetFlags},
+    fs::utils::{XattrName, XattrNamespace, XattrSetFlags},
     prelude::*,
 };
 
@@ -27,8 +27,8 @@ struct RamXattrInner {
     map: HashMap<RamXattrName, RamXattrValue>,
     total_name_count: usize,
     total_name_len: usize,
-    user_name_count: usize,
-    user_name_len: usize,
+    trusted_name_count: usize,
+    trusted_name_len: usize,
 }
 
 impl RamXattr {
@@ -76,9 +76,9 @@ impl RamXattr {
 
                 xattr.total_name_count += 1;
                 xattr.total_name_len += name_len;
-                if namespace.is_user() {
-                    xattr.user_name_count += 1;
-                    xattr.user_name_len += name_len;
+                if namespace.is_admin() {
+                    xattr.trusted_name_count += 1;
+                    xattr.trusted_name_len += name_len;
                 }
             }
         };
@@ -118,10 +118,11 @@ impl RamXattr {
         let xattr = inner.read();
 
         // Include the null byte following each name
-        let list_actual_len = if namespace.is_user() {
-            xattr.user_name_len + xattr.user_name_count
-        } else {
+        let list_actual_len = if namespace.is_admin() {
             xattr.total_name_len + xattr.total_name_count
+        } else {
+            (xattr.total_name_len - xattr.trusted_name_len)
+                + (xattr.total_name_count - xattr.trusted_name_count)
         };
         let list_avail_len = list_writer.avail();
         if list_avail_len == 0 {
@@ -132,7 +133,7 @@ impl RamXattr {
         }
 
         for (name, _) in &xattr.map {
-            if namespace.is_user() && !name.namespace.is_user() {
+            if !name.namespace.is_listed_in(namespace) {
                 continue;
             }
 
@@ -158,23 +159,12 @@ impl RamXattr {
         let name_len = name.full_name_len();
         xattr.total_name_count -= 1;
         xattr.total_name_len -= name_len;
-        if namespace.is_user() {
-            xattr.user_name_count -= 1;
-            xattr.user_name_len -= name_len;
+        if namespace.is_admin() {
+            xattr.trusted_name_count -= 1;
+            xattr.trusted_name_len -= name_len;
         }
         Ok(())
     }
-
-    /// Checks if the file type is valid for xattr support.
-    pub fn check_file_type_for_xattr(file_type: InodeType) -> Result<()> {
-        match file_type {
-            InodeType::File | InodeType::Dir => Ok(()),
-            _ => Err(Error::with_message(
-                Errno::EPERM,
-                "xattr is not supported on the file type",
-            )),
-        }
-    }
 }
 
 impl From<XattrName<'_>> for RamXattrName {
@@ -198,8 +188,8 @@ impl RamXattrInner {
             map: HashMap::new(),
             total_name_count: 0,
             total_name_len: 0,
-            user_name_count: 0,
-            user_name_len: 0,
+            trusted_name_count: 0,
+            trusted_name_len: 0,
         }
     }
 }
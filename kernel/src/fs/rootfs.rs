@@ -7,6 +7,7 @@ use libflate::gzip::Decoder as GZipDecoder;
 use spin::Once;
 
 use super::{
+    devtmpfs,
     fs_resolver::{FsPath, FsResolver},
     path::MountNode,
     procfs::{self, ProcFS},
@@ -111,9 +112,9 @@ pub fn init(initramfs_buf: &[u8]) -> Result<()> {
     // Mount ProcFS
     let proc_dentry = fs.lookup(&FsPath::try_from("/proc")?)?;
     proc_dentry.mount(ProcFS::new())?;
-    // Mount DevFS
+    // Mount DevTmpFS
     let dev_dentry = fs.lookup(&FsPath::try_from("/dev")?)?;
-    dev_dentry.mount(RamFS::new())?;
+    devtmpfs::init(&dev_dentry)?;
     // Mount SysFS
     let sys_dentry = fs.lookup(&FsPath::try_from("/sys")?)?;
     sysfs_init();
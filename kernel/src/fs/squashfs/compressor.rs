@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core2::io::Read;
+use libflate::zlib::Decoder as ZlibDecoder;
+use ruzstd::FrameDecoder as ZstdDecoder;
+
+use crate::prelude::*;
+
+/// The compressors of the metadata and the data blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Compressor {
+    /// The zlib format of DEFLATE, which is the default of `mksquashfs`.
+    Zlib,
+    Zstd,
+}
+
+impl TryFrom<u16> for Compressor {
+    type Error = Error;
+
+    fn try_from(id: u16) -> Result<Self> {
+        match id {
+            1 => Ok(Self::Zlib),
+            6 => Ok(Self::Zstd),
+            // LZMA, LZO, XZ, and LZ4.
+            2..=5 => return_errno_with_message!(Errno::EINVAL, "the compressor is not supported"),
+            _ => return_errno_with_message!(Errno::EINVAL, "the compressor is unknown"),
+        }
+    }
+}
+
+impl Compressor {
+    /// Decompresses a block, whose decompressed size must not exceed `max_len`.
+    pub(super) fn decompress(&self, input: &[u8], max_len: usize) -> Result<Vec<u8>> {
+        let mut output = vec![0u8; max_len];
+        let len = match self {
+            Self::Zlib => {
+                let mut decoder = ZlibDecoder::new(input)
+                    .map_err(|_| Error::with_message(Errno::EIO, "the zlib header is invalid"))?;
+                let mut len = 0;
+                loop {
+                    if len == max_len {
+                        // The decompressed data must end exactly at the limit.
+                        let mut byte = [0u8; 1];
+                        if decoder.read(&mut byte).map_or(true, |n| n != 0) {
+                            return_errno_with_message!(Errno::EIO, "the block is too large");
+                        }
+                        break;
+                    }
+                    match decoder.read(&mut output[len..]) {
+                        Ok(0) => break,
+                        Ok(n) => len += n,
+                        Err(_) => {
+                            return_errno_with_message!(Errno::EIO, "the zlib block is corrupted")
+                        }
+                    }
+                }
+                len
+            }
+            Self::Zstd => ZstdDecoder::new()
+                .decode_all(input, &mut output)
+                .map_err(|_| Error::with_message(Errno::EIO, "the zstd block is corrupted"))?,
+        };
+
+        output.truncate(len);
+        Ok(output)
+    }
+}
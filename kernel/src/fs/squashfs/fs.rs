@@ -0,0 +1,364 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::num::NonZeroUsize;
+
+use aster_block::BlockDevice;
+use hashbrown::HashMap;
+use lru::LruCache;
+use ostd::mm::VmIo;
+use spin::Once;
+
+use super::{
+    inode::SquashInode,
+    super_block::{InodeRef, RawSuperBlock, SuperBlock, SuperBlockFlags, SQUASHFS_MAGIC},
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock as VfsSuperBlock},
+    prelude::*,
+};
+
+/// The maximum size of the decompressed metadata blocks.
+pub(super) const META_BLOCK_SIZE: usize = 8192;
+/// The maximum length of the names.
+const MAX_NAME_LEN: usize = 256;
+
+/// If this bit of the header of a metadata block is set, the block is not compressed.
+const META_UNCOMPRESSED: u16 = 1 << 15;
+/// If this bit of the size of a data block or a fragment block is set, the block is not
+/// compressed.
+pub(super) const DATA_UNCOMPRESSED: u32 = 1 << 24;
+
+/// The number of the cached metadata blocks.
+const META_CACHE_SIZE: usize = 64;
+/// The number of the cached data blocks and fragment blocks.
+const BLOCK_CACHE_SIZE: usize = 16;
+
+/// The size of an entry in the ID table.
+const ID_ENTRY_SIZE: usize = 4;
+/// The size of an entry in the fragment table.
+const FRAGMENT_ENTRY_SIZE: usize = 16;
+/// The size of an entry in the export table.
+const EXPORT_ENTRY_SIZE: usize = 8;
+
+/// A read-only SquashFS 4.0 file system, which is used by compressed root file systems and
+/// Live images.
+pub struct SquashFS {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: SuperBlock,
+    /// The decompressed metadata blocks, indexed by their device offsets.
+    meta_cache: Mutex<LruCache<u64, Arc<MetaBlock>>>,
+    /// The decompressed data blocks and fragment blocks, indexed by their device offsets.
+    block_cache: Mutex<LruCache<u64, Arc<Vec<u8>>>>,
+    /// The user and group IDs, which are referenced by the inodes.
+    ids: Vec<u32>,
+    /// The locations and the sizes of the fragment blocks.
+    fragments: Vec<(u64, u32)>,
+    /// The device offsets of the metadata blocks of the export table.
+    export_blocks: Vec<u64>,
+    root: Once<Arc<SquashInode>>,
+    /// The opened inodes, indexed by the inode numbers.
+    inodes: RwMutex<HashMap<u32, Arc<SquashInode>>>,
+}
+
+/// A decompressed metadata block.
+pub(super) struct MetaBlock {
+    pub(super) data: Vec<u8>,
+    /// The device offset of the next metadata block.
+    pub(super) next: u64,
+}
+
+impl SquashFS {
+    /// Opens a SquashFS volume on the `block_device`.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let mut raw_super_block = [0u8; size_of::<RawSuperBlock>()];
+        block_device.read_bytes(0, &mut raw_super_block)?;
+        let super_block = SuperBlock::try_from(RawSuperBlock::from_bytes(&raw_super_block))?;
+
+        let mut fs = Self {
+            block_device,
+            super_block,
+            meta_cache: Mutex::new(LruCache::new(NonZeroUsize::new(META_CACHE_SIZE).unwrap())),
+            block_cache: Mutex::new(LruCache::new(NonZeroUsize::new(BLOCK_CACHE_SIZE).unwrap())),
+            ids: Vec::new(),
+            fragments: Vec::new(),
+            export_blocks: Vec::new(),
+            root: Once::new(),
+            inodes: RwMutex::new(HashMap::new()),
+        };
+
+        fs.ids = fs
+            .read_table(
+                super_block.id_table_start,
+                super_block.id_count as usize,
+                ID_ENTRY_SIZE,
+            )?
+            .chunks_exact(ID_ENTRY_SIZE)
+            .map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+            .collect();
+        if !super_block.flags.contains(SuperBlockFlags::NO_FRAGMENTS) {
+            fs.fragments = fs
+                .read_table(
+                    super_block.fragment_table_start,
+                    super_block.fragment_count as usize,
+                    FRAGMENT_ENTRY_SIZE,
+                )?
+                .chunks_exact(FRAGMENT_ENTRY_SIZE)
+                .map(|entry| {
+                    let start = u64::from_le_bytes(entry[..8].try_into().unwrap());
+                    let size = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+                    (start, size)
+                })
+                .collect();
+        }
+        if let Some(export_table_start) = super_block.export_table_start {
+            let len = super_block.inode_count as usize * EXPORT_ENTRY_SIZE;
+            fs.export_blocks = fs.read_table_pointers(export_table_start, len)?;
+        }
+
+        let fs = Arc::new(fs);
+        let root = SquashInode::new(&fs, super_block.root_inode, None)?;
+        fs.insert_inode(root.ino() as u32, root.clone());
+        fs.root.call_once(|| root);
+        Ok(fs)
+    }
+
+    /// Reads a table of `count` entries, which is stored in the metadata blocks whose device
+    /// offsets are listed at `start`.
+    fn read_table(&self, start: u64, count: usize, entry_size: usize) -> Result<Vec<u8>> {
+        let len = count * entry_size;
+        let mut table = Vec::with_capacity(len);
+        for block in self.read_table_pointers(start, len)? {
+            table.extend_from_slice(&self.read_meta_block(block)?.data);
+        }
+        if table.len() < len {
+            return_errno_with_message!(Errno::EIO, "the SquashFS table is truncated");
+        }
+        table.truncate(len);
+        Ok(table)
+    }
+
+    fn read_table_pointers(&self, start: u64, len: usize) -> Result<Vec<u64>> {
+        let num_blocks = len.div_ceil(META_BLOCK_SIZE);
+        let mut pointers = vec![0u8; num_blocks * size_of::<u64>()];
+        self.read_raw(start, &mut pointers)?;
+        Ok(pointers
+            .chunks_exact(size_of::<u64>())
+            .map(|pointer| u64::from_le_bytes(pointer.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Reads the raw bytes at `offset`, which must be within the volume.
+    fn read_raw(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        if offset
+            .checked_add(buf.len() as u64)
+            .is_none_or(|end| end > self.super_block.bytes_used)
+        {
+            return_errno_with_message!(Errno::EIO, "the SquashFS block is beyond the volume");
+        }
+        self.block_device.read_bytes(offset as usize, buf)?;
+        Ok(())
+    }
+
+    /// Reads the metadata block at the device `offset`.
+    pub(super) fn read_meta_block(&self, offset: u64) -> Result<Arc<MetaBlock>> {
+        if let Some(block) = self.meta_cache.lock().get(&offset) {
+            return Ok(block.clone());
+        }
+
+        let mut header = [0u8; 2];
+        self.read_raw(offset, &mut header)?;
+        let header = u16::from_le_bytes(header);
+        let len = (header & !META_UNCOMPRESSED) as usize;
+        if len == 0 || len > META_BLOCK_SIZE {
+            return_errno_with_message!(Errno::EIO, "the SquashFS metadata block is invalid");
+        }
+
+        let mut raw = vec![0u8; len];
+        self.read_raw(offset + 2, &mut raw)?;
+        let data = if header & META_UNCOMPRESSED != 0 {
+            raw
+        } else {
+            self.super_block
+                .compressor
+                .decompress(&raw, META_BLOCK_SIZE)?
+        };
+
+        let block = Arc::new(MetaBlock {
+            data,
+            next: offset + 2 + len as u64,
+        });
+        self.meta_cache.lock().put(offset, block.clone());
+        Ok(block)
+    }
+
+    /// Reads a data block or a fragment block at the device `offset`, whose size on the device
+    /// is encoded in `size`.
+    ///
+    /// The blocks of size zero are the holes of the sparse files, which are filled with zeros.
+    pub(super) fn read_data_block(&self, offset: u64, size: u32) -> Result<Arc<Vec<u8>>> {
+        let len = (size & !DATA_UNCOMPRESSED) as usize;
+        if len == 0 {
+            return Ok(Arc::new(vec![0u8; self.super_block.block_size]));
+        }
+        if let Some(block) = self.block_cache.lock().get(&offset) {
+            return Ok(block.clone());
+        }
+        if len > self.super_block.block_size {
+            return_errno_with_message!(Errno::EIO, "the SquashFS data block is invalid");
+        }
+
+        let mut raw = vec![0u8; len];
+        self.read_raw(offset, &mut raw)?;
+        let data = if size & DATA_UNCOMPRESSED != 0 {
+            raw
+        } else {
+            self.super_block
+                .compressor
+                .decompress(&raw, self.super_block.block_size)?
+        };
+
+        let block = Arc::new(data);
+        self.block_cache.lock().put(offset, block.clone());
+        Ok(block)
+    }
+
+    /// Reads the fragment block of `index`, which holds the tails of several files.
+    pub(super) fn read_fragment(&self, index: u32) -> Result<Arc<Vec<u8>>> {
+        let &(start, size) = self
+            .fragments
+            .get(index as usize)
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the fragment does not exist"))?;
+        self.read_data_block(start, size)
+    }
+
+    /// Returns the user or group ID of `index` in the ID table.
+    pub(super) fn id(&self, index: u16) -> Result<u32> {
+        self.ids
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the ID does not exist"))
+    }
+
+    /// Returns the inode of the inode number `ino` with the export table.
+    ///
+    /// This allows the inodes to be found without their paths, e.g., when the file handles are
+    /// decoded or the parents of the directories are looked up.
+    pub fn inode_by_ino(self: &Arc<Self>, ino: u32) -> Result<Arc<SquashInode>> {
+        if let Some(inode) = self.find_inode(ino) {
+            return Ok(inode);
+        }
+        if self.export_blocks.is_empty() {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the file system is not exportable");
+        }
+        if ino == 0 || ino > self.super_block.inode_count {
+            return_errno_with_message!(Errno::ESTALE, "the inode number is invalid");
+        }
+
+        let offset = (ino - 1) as usize * EXPORT_ENTRY_SIZE;
+        let block = self.read_meta_block(self.export_blocks[offset / META_BLOCK_SIZE])?;
+        let entry = block
+            .data
+            .get(offset % META_BLOCK_SIZE..offset % META_BLOCK_SIZE + EXPORT_ENTRY_SIZE)
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the export table is truncated"))?;
+        let inode_ref = InodeRef(u64::from_le_bytes(entry.try_into().unwrap()));
+
+        let inode = SquashInode::new(self, inode_ref, None)?;
+        if inode.ino() != ino as u64 {
+            return_errno_with_message!(Errno::EIO, "the export table is corrupted");
+        }
+        self.insert_inode(ino, inode.clone());
+        Ok(inode)
+    }
+
+    pub(super) fn super_block(&self) -> &SuperBlock {
+        &self.super_block
+    }
+
+    pub(super) fn root(&self) -> &Arc<SquashInode> {
+        self.root.get().unwrap()
+    }
+
+    pub(super) fn find_inode(&self, ino: u32) -> Option<Arc<SquashInode>> {
+        self.inodes.read().get(&ino).cloned()
+    }
+
+    pub(super) fn insert_inode(&self, ino: u32, inode: Arc<SquashInode>) {
+        self.inodes.write().insert(ino, inode);
+    }
+}
+
+/// A reader of the data that spans the consecutive metadata blocks.
+pub(super) struct MetaReader<'a> {
+    fs: &'a SquashFS,
+    block: Arc<MetaBlock>,
+    /// The offset within the current block.
+    offset: usize,
+}
+
+impl<'a> MetaReader<'a> {
+    /// Creates a reader at `offset` within the metadata block at the device offset `block`.
+    pub(super) fn new(fs: &'a SquashFS, block: u64, offset: usize) -> Result<Self> {
+        let block = fs.read_meta_block(block)?;
+        if offset > block.data.len() {
+            return_errno_with_message!(Errno::EIO, "the metadata offset is invalid");
+        }
+        Ok(Self { fs, block, offset })
+    }
+
+    pub(super) fn read(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            if self.offset == self.block.data.len() {
+                self.block = self.fs.read_meta_block(self.block.next)?;
+                self.offset = 0;
+            }
+            let len = (buf.len() - pos).min(self.block.data.len() - self.offset);
+            buf[pos..pos + len].copy_from_slice(&self.block.data[self.offset..self.offset + len]);
+            pos += len;
+            self.offset += len;
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_val<T: Pod>(&mut self) -> Result<T> {
+        let mut val = T::new_zeroed();
+        self.read(val.as_bytes_mut())?;
+        Ok(val)
+    }
+
+    pub(super) fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.read(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl FileSystem for SquashFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root().clone()
+    }
+
+    fn sb(&self) -> VfsSuperBlock {
+        let sb = &self.super_block;
+        let mut vfs_sb = VfsSuperBlock::new(SQUASHFS_MAGIC as u64, sb.block_size, MAX_NAME_LEN);
+        vfs_sb.blocks = (sb.bytes_used as usize).div_ceil(sb.block_size);
+        vfs_sb.files = sb.inode_count as usize;
+        vfs_sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+impl Debug for SquashFS {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SquashFS")
+            .field("super_block", &self.super_block)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,734 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{ops::Range, time::Duration};
+
+use align_ext::AlignExt;
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use spin::Once;
+
+use super::{
+    fs::{MetaReader, SquashFS, DATA_UNCOMPRESSED},
+    super_block::InodeRef,
+};
+use crate::{
+    fs::{
+        device::{Device, DeviceId},
+        path::is_dot,
+        utils::{
+            CachePage, DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType,
+            PageCache, PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The number of the `.` and `..` entries, which are emitted first by `readdir_at`.
+const NUM_SPECIAL_ENTRIES: usize = 2;
+
+/// The index of the fragment of the files whose tails are not in fragments.
+const NO_FRAGMENT: u32 = u32::MAX;
+
+/// The size of the directories is recorded with the 3 bytes of the `.` and `..` entries.
+const DIR_SIZE_OFFSET: usize = 3;
+/// The maximum number of the entries that share a directory header.
+const MAX_ENTRIES_PER_HEADER: u32 = 256;
+
+/// The header of all the inodes.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawInodeHeader {
+    type_: u16,
+    permissions: u16,
+    uid_index: u16,
+    gid_index: u16,
+    mtime: u32,
+    inode_number: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDirInode {
+    start_block: u32,
+    nlinks: u32,
+    size: u16,
+    offset: u16,
+    parent_inode: u32,
+}
+
+/// The directory inode with the index and the extended attributes, which is used by the
+/// large directories.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+struct RawExtDirInode {
+    nlinks: u32,
+    size: u32,
+    start_block: u32,
+    parent_inode: u32,
+    index_count: u16,
+    offset: u16,
+    xattr_index: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawFileInode {
+    blocks_start: u32,
+    fragment: u32,
+    fragment_offset: u32,
+    size: u32,
+}
+
+/// The file inode with the 64-bit fields, which is used by the large files, the sparse files,
+/// and the hard links.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+struct RawExtFileInode {
+    blocks_start: u64,
+    size: u64,
+    sparse: u64,
+    nlinks: u32,
+    fragment: u32,
+    fragment_offset: u32,
+    xattr_index: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawSymlinkInode {
+    nlinks: u32,
+    target_size: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDeviceInode {
+    nlinks: u32,
+    device: u32,
+}
+
+/// The header of a run of the directory entries, whose inodes are in the same metadata block.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDirHeader {
+    /// The number of the entries minus one.
+    count: u32,
+    /// The metadata block of the inodes, relative to the start of the inode table.
+    start_block: u32,
+    /// The base of the inode numbers.
+    inode_number: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDirEntry {
+    /// The offset of the inode within the metadata block.
+    offset: u16,
+    /// The difference of the inode number from the base, which is signed.
+    inode_offset: u16,
+    type_: u16,
+    /// The length of the name minus one.
+    name_size: u16,
+}
+
+/// Converts the basic or the extended type of an inode to the inode type.
+fn inode_type(type_: u16) -> Result<InodeType> {
+    let type_ = match type_ {
+        1 | 8 => InodeType::Dir,
+        2 | 9 => InodeType::File,
+        3 | 10 => InodeType::SymLink,
+        4 | 11 => InodeType::BlockDevice,
+        5 | 12 => InodeType::CharDevice,
+        6 | 13 => InodeType::NamedPipe,
+        7 | 14 => InodeType::Socket,
+        _ => return_errno_with_message!(Errno::EIO, "the SquashFS inode type is invalid"),
+    };
+    Ok(type_)
+}
+
+/// An inode of the SquashFS file system.
+///
+/// The inodes are never changed, so all the metadata is loaded when the inodes are created.
+pub struct SquashInode {
+    ino: u64,
+    type_: InodeType,
+    meta: InodeMeta,
+    data: InodeData,
+    /// The parent directory that the inode is looked up from.
+    ///
+    /// It is `None` for the root directory and the inodes that are found with the export
+    /// table, whose parents are found with the export table too.
+    parent: Option<Arc<SquashInode>>,
+    page_cache: Option<PageCache>,
+    fs: Weak<SquashFS>,
+    this: Weak<SquashInode>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InodeMeta {
+    size: usize,
+    mode: InodeMode,
+    nlinks: usize,
+    uid: Uid,
+    gid: Gid,
+    mtime: Duration,
+    rdev: u64,
+}
+
+enum InodeData {
+    Dir {
+        /// The metadata block of the entries, relative to the start of the directory table.
+        start_block: u32,
+        /// The offset of the entries within the metadata block.
+        offset: usize,
+        /// The inode number of the parent directory.
+        parent_ino: u32,
+        /// The entries, which are read when they are first used.
+        entries: Once<Vec<DirEntry>>,
+    },
+    File {
+        /// The device offsets and the encoded sizes of the data blocks.
+        blocks: Vec<(u64, u32)>,
+        /// The fragment and the offset within it that hold the tail of the file.
+        fragment: Option<(u32, usize)>,
+    },
+    Symlink(String),
+    Special,
+}
+
+/// An entry of a directory.
+struct DirEntry {
+    name: String,
+    ino: u32,
+    type_: InodeType,
+    inode_ref: InodeRef,
+}
+
+impl SquashInode {
+    /// Loads the inode of `inode_ref`.
+    pub(super) fn new(
+        fs: &Arc<SquashFS>,
+        inode_ref: InodeRef,
+        parent: Option<Arc<Self>>,
+    ) -> Result<Arc<Self>> {
+        let sb = fs.super_block();
+        let mut reader = MetaReader::new(
+            fs,
+            sb.inode_table_start + inode_ref.block(),
+            inode_ref.offset(),
+        )?;
+        let header: RawInodeHeader = reader.read_val()?;
+        let raw_type = header.type_;
+        let type_ = inode_type(raw_type)?;
+
+        let mut meta = InodeMeta {
+            size: 0,
+            mode: InodeMode::from_bits_truncate(header.permissions),
+            nlinks: 1,
+            uid: Uid::new(fs.id(header.uid_index)?),
+            gid: Gid::new(fs.id(header.gid_index)?),
+            mtime: Duration::from_secs(header.mtime as u64),
+            rdev: 0,
+        };
+
+        let data = match raw_type {
+            1 => {
+                let dir: RawDirInode = reader.read_val()?;
+                meta.nlinks = dir.nlinks as usize;
+                meta.size = dir.size as usize;
+                Self::dir_data(dir.start_block, dir.offset, dir.parent_inode)
+            }
+            8 => {
+                let dir: RawExtDirInode = reader.read_val()?;
+                meta.nlinks = dir.nlinks as usize;
+                meta.size = dir.size as usize;
+                Self::dir_data(dir.start_block, dir.offset, dir.parent_inode)
+            }
+            2 => {
+                let file: RawFileInode = reader.read_val()?;
+                meta.size = file.size as usize;
+                let (fragment, fragment_offset) = (file.fragment, file.fragment_offset);
+                Self::file_data(
+                    fs,
+                    &mut reader,
+                    file.blocks_start as u64,
+                    meta.size,
+                    fragment,
+                    fragment_offset,
+                )?
+            }
+            9 => {
+                let file: RawExtFileInode = reader.read_val()?;
+                meta.nlinks = file.nlinks as usize;
+                meta.size = usize::try_from(file.size).map_err(|_| {
+                    Error::with_message(Errno::EFBIG, "the SquashFS file is too large")
+                })?;
+                let (fragment, fragment_offset) = (file.fragment, file.fragment_offset);
+                Self::file_data(
+                    fs,
+                    &mut reader,
+                    file.blocks_start,
+                    meta.size,
+                    fragment,
+                    fragment_offset,
+                )?
+            }
+            3 | 10 => {
+                let symlink: RawSymlinkInode = reader.read_val()?;
+                meta.nlinks = symlink.nlinks as usize;
+                meta.size = symlink.target_size as usize;
+                if meta.size > PAGE_SIZE {
+                    return_errno_with_message!(Errno::EIO, "the symlink target is too long");
+                }
+                let target = reader.read_vec(meta.size)?;
+                InodeData::Symlink(String::from_utf8_lossy(&target).into_owned())
+            }
+            4 | 5 | 11 | 12 => {
+                let device: RawDeviceInode = reader.read_val()?;
+                meta.nlinks = device.nlinks as usize;
+                // The device numbers are encoded in the same way as Linux's `new_encode_dev`.
+                let device = device.device;
+                let major = (device & 0xF_FF00) >> 8;
+                let minor = (device & 0xFF) | ((device >> 12) & 0xF_FF00);
+                meta.rdev = DeviceId::new(major, minor).into();
+                InodeData::Special
+            }
+            _ => {
+                meta.nlinks = reader.read_val::<u32>()? as usize;
+                InodeData::Special
+            }
+        };
+
+        Ok(Arc::new_cyclic(|weak_self: &Weak<Self>| Self {
+            ino: header.inode_number as u64,
+            type_,
+            meta,
+            page_cache: matches!(data, InodeData::File { .. })
+                .then(|| PageCache::with_capacity(meta.size, weak_self.clone() as _).unwrap()),
+            data,
+            parent,
+            fs: Arc::downgrade(fs),
+            this: weak_self.clone(),
+        }))
+    }
+
+    fn dir_data(start_block: u32, offset: u16, parent_ino: u32) -> InodeData {
+        InodeData::Dir {
+            start_block,
+            offset: offset as usize,
+            parent_ino,
+            entries: Once::new(),
+        }
+    }
+
+    fn file_data(
+        fs: &SquashFS,
+        reader: &mut MetaReader,
+        blocks_start: u64,
+        size: usize,
+        fragment: u32,
+        fragment_offset: u32,
+    ) -> Result<InodeData> {
+        let block_size = fs.super_block().block_size;
+        // The tail of the file is in a fragment unless the file has no fragment.
+        let num_blocks = if fragment == NO_FRAGMENT {
+            size.div_ceil(block_size)
+        } else {
+            size / block_size
+        };
+
+        let mut blocks = Vec::with_capacity(num_blocks);
+        let mut offset = blocks_start;
+        for _ in 0..num_blocks {
+            let size: u32 = reader.read_val()?;
+            blocks.push((offset, size));
+            offset += (size & !DATA_UNCOMPRESSED) as u64;
+        }
+
+        let fragment = (fragment != NO_FRAGMENT).then_some((fragment, fragment_offset as usize));
+        Ok(InodeData::File { blocks, fragment })
+    }
+
+    fn fs(&self) -> Arc<SquashFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn is_root(&self) -> bool {
+        self.ino == self.fs().root().ino
+    }
+
+    /// Returns the inode number of the parent directory.
+    fn parent_ino(&self) -> u64 {
+        match &self.data {
+            InodeData::Dir { parent_ino, .. } if !self.is_root() => *parent_ino as u64,
+            _ => self.ino,
+        }
+    }
+
+    fn parent(&self) -> Result<Arc<Self>> {
+        if let Some(parent) = &self.parent {
+            return Ok(parent.clone());
+        }
+        let fs = self.fs();
+        if self.is_root() {
+            return Ok(fs.root().clone());
+        }
+        fs.inode_by_ino(self.parent_ino() as u32)
+    }
+
+    fn entries(&self) -> Result<&[DirEntry]> {
+        let InodeData::Dir {
+            start_block,
+            offset,
+            entries,
+            ..
+        } = &self.data
+        else {
+            return_errno!(Errno::ENOTDIR);
+        };
+        if let Some(entries) = entries.get() {
+            return Ok(entries);
+        }
+
+        let fs = self.fs();
+        let mut reader = MetaReader::new(
+            &fs,
+            fs.super_block().directory_table_start + *start_block as u64,
+            *offset,
+        )?;
+        let mut remaining = self.meta.size.saturating_sub(DIR_SIZE_OFFSET);
+        let consume = |remaining: &mut usize, len: usize| -> Result<()> {
+            *remaining = remaining.checked_sub(len).ok_or_else(|| {
+                Error::with_message(Errno::EIO, "the SquashFS directory is corrupted")
+            })?;
+            Ok(())
+        };
+
+        let mut new_entries = Vec::new();
+        while remaining > 0 {
+            let header: RawDirHeader = reader.read_val()?;
+            consume(&mut remaining, size_of::<RawDirHeader>())?;
+            if header.count >= MAX_ENTRIES_PER_HEADER {
+                return_errno_with_message!(Errno::EIO, "the SquashFS directory is corrupted");
+            }
+
+            for _ in 0..=header.count {
+                let entry: RawDirEntry = reader.read_val()?;
+                let name = reader.read_vec(entry.name_size as usize + 1)?;
+                consume(&mut remaining, size_of::<RawDirEntry>() + name.len())?;
+
+                let ino = header
+                    .inode_number
+                    .wrapping_add(entry.inode_offset as i16 as u32);
+                new_entries.push(DirEntry {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    ino,
+                    type_: inode_type(entry.type_)?,
+                    inode_ref: InodeRef::new(header.start_block as u64, entry.offset),
+                });
+            }
+        }
+
+        Ok(entries.call_once(|| new_entries))
+    }
+
+    /// Returns the block that holds the page `idx` and the range of the page in the block.
+    fn page_block(&self, idx: usize) -> Result<(Arc<Vec<u8>>, Range<usize>)> {
+        let InodeData::File { blocks, fragment } = &self.data else {
+            return_errno!(Errno::EISDIR);
+        };
+        let fs = self.fs();
+        let block_size = fs.super_block().block_size;
+
+        // The blocks are larger than the pages, so a page is always in a single block.
+        let offset = idx * PAGE_SIZE;
+        let block_index = offset / block_size;
+        let block_start = block_index * block_size;
+        let block_len = (self.meta.size - block_start).min(block_size);
+        let range = offset - block_start..(offset - block_start + PAGE_SIZE).min(block_len);
+
+        if let Some(&(device_offset, size)) = blocks.get(block_index) {
+            return Ok((fs.read_data_block(device_offset, size)?, range));
+        }
+        let Some((index, fragment_offset)) = fragment else {
+            return_errno_with_message!(Errno::EIO, "the SquashFS block does not exist");
+        };
+        let fragment = fs.read_fragment(*index)?;
+        Ok((
+            fragment,
+            fragment_offset + range.start..fragment_offset + range.end,
+        ))
+    }
+}
+
+impl PageCacheBackend for SquashInode {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let (block, range) = self.page_block(idx)?;
+        let data = block
+            .get(range)
+            .ok_or_else(|| Error::with_message(Errno::EIO, "the SquashFS block is truncated"))?;
+
+        // The blocks are decompressed synchronously, so the pages are ready when this returns.
+        frame.writer().fill(0u8);
+        frame.writer().write(&mut VmReader::from(data));
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page_async(&self, _idx: usize, _frame: &CachePage) -> Result<BioWaiter> {
+        // The pages are never dirty, since the files cannot be written.
+        return_errno!(Errno::EROFS)
+    }
+
+    fn npages(&self) -> usize {
+        self.meta.size.align_up(PAGE_SIZE) / PAGE_SIZE
+    }
+}
+
+impl Inode for SquashInode {
+    fn size(&self) -> usize {
+        self.meta.size
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let meta = &self.meta;
+        let block_size = self.fs().super_block().block_size;
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: meta.size,
+            blk_size: block_size,
+            blocks: meta.size.div_ceil(512),
+            atime: meta.mtime,
+            mtime: meta.mtime,
+            ctime: meta.mtime,
+            type_: self.type_,
+            mode: meta.mode,
+            nlinks: meta.nlinks,
+            uid: meta.uid,
+            gid: meta.gid,
+            rdev: meta.rdev,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.meta.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.meta.uid)
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.meta.gid)
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn atime(&self) -> Duration {
+        self.meta.mtime
+    }
+
+    // Only the modification times are recorded, and they are never updated.
+    fn set_atime(&self, _time: Duration) {}
+
+    fn mtime(&self) -> Duration {
+        self.meta.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.meta.mtime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            if self.type_ == InodeType::Dir {
+                return_errno!(Errno::EISDIR);
+            }
+            return_errno!(Errno::EINVAL);
+        };
+
+        let (read_offset, read_len) = {
+            let size = self.meta.size;
+            let start = size.min(offset);
+            let end = size.min(offset.saturating_add(writer.avail()));
+            (start, end - start)
+        };
+        page_cache
+            .pages()
+            .read(read_offset, writer.limit(read_len))?;
+
+        Ok(read_len)
+    }
+
+    // The data are always decompressed into the page cache, so direct I/O falls back to
+    // buffered I/O.
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn write_direct_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode, _type_: MknodType) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        if !self.type_.is_device() {
+            return None;
+        }
+        crate::device::get_device(self.meta.rdev as usize).ok()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let entries = self.entries()?;
+
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            if *offset == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, 0)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", self.parent_ino(), InodeType::Dir, 1)?;
+                *offset += 1;
+            }
+
+            for entry in entries.iter().skip(*offset - NUM_SPECIAL_ENTRIES) {
+                visitor.visit(&entry.name, entry.ino as u64, entry.type_, *offset)?;
+                *offset += 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let entries = self.entries()?;
+        if is_dot(name) {
+            return Ok(self.this());
+        }
+        if name == ".." {
+            return Ok(self.parent()?);
+        }
+
+        // The entries are sorted by their names.
+        let index = entries
+            .binary_search_by(|entry| entry.name.as_str().cmp(name))
+            .map_err(|_| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        let entry = &entries[index];
+
+        let fs = self.fs();
+        if let Some(inode) = fs.find_inode(entry.ino) {
+            return Ok(inode);
+        }
+        let inode = Self::new(&fs, entry.inode_ref, Some(self.this()))?;
+        fs.insert_inode(entry.ino, inode.clone());
+        Ok(inode)
+    }
+
+    fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.data {
+            InodeData::Symlink(target) => Ok(target.clone()),
+            _ => return_errno_with_message!(Errno::EINVAL, "the inode is not a symlink"),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+}
+
+impl Debug for SquashInode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SquashInode")
+            .field("ino", &self.ino)
+            .field("type_", &self.type_)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,402 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SquashFS file system, which is a read-only compressed file system.
+//!
+//! SquashFS is used by compressed root file systems and Live images, whose sizes are much
+//! smaller than those of the uncompressed images.
+//!
+//! The features are as follows:
+//! 1. The metadata blocks and the data blocks that are compressed with zlib or zstd.
+//! 2. The fragments, which pack the tails of the files into shared blocks.
+//! 3. The sparse files, the hard links, the symlinks, and the special files.
+//! 4. The export table, which finds the inodes by their inode numbers, e.g., to look up the
+//!    parents of the directories that are not reached from the root.
+//!
+//! # Limitation
+//!
+//! Here we summarize the features that need to be implemented in the future.
+//! 1. Supports the LZMA, LZO, XZ, and LZ4 compressors.
+//! 2. Supports the extended attributes.
+//! 3. Uses the directory indexes to look up the names in the large directories.
+//! 4. Decompresses the blocks directly into the page cache, rather than into the caches of
+//!    the decompressed blocks.
+
+pub use fs::SquashFS;
+pub use inode::SquashInode;
+
+mod compressor;
+mod fs;
+mod inode;
+mod super_block;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        fs::{
+            device::DeviceId,
+            utils::{FileSystem, Inode, InodeType, MemoryDisk},
+        },
+        prelude::*,
+        process::{Gid, Uid},
+    };
+
+    const BLOCK_SIZE: usize = 4096;
+    const DATA_UNCOMPRESSED: u32 = 1 << 24;
+    const META_UNCOMPRESSED: u16 = 1 << 15;
+    const NO_FRAGMENT: u32 = u32::MAX;
+
+    const COMPRESSOR_ZLIB: u16 = 1;
+    const COMPRESSOR_XZ: u16 = 4;
+    const COMPRESSOR_ZSTD: u16 = 6;
+
+    const MTIME: u32 = 1_700_000_000;
+    const NUM_INODES: u32 = 8;
+
+    const ROOT_INO: u32 = 1;
+    const BIG_INO: u32 = 2;
+    const DIR_INO: u32 = 3;
+    const HELLO_INO: u32 = 4;
+    const LINK_INO: u32 = 5;
+    const NULL_INO: u32 = 6;
+    const SPARSE_INO: u32 = 7;
+    const NESTED_INO: u32 = 8;
+
+    const HELLO: &[u8] = b"Hello, SquashFS!";
+    const NESTED: &[u8] = b"nested";
+    /// The size of `big`, whose tail is in the fragment.
+    const BIG_SIZE: usize = BLOCK_SIZE + 100;
+
+    fn big_data() -> Vec<u8> {
+        (0..BIG_SIZE).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Compresses the data with stored DEFLATE blocks, which are valid zlib data.
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![0x78, 0x01];
+        let chunks: Vec<&[u8]> = data.chunks(u16::MAX as usize).collect();
+        for (index, chunk) in chunks.iter().enumerate() {
+            output.push((index == chunks.len() - 1) as u8);
+            output.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            output.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+            output.extend_from_slice(chunk);
+        }
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        output
+    }
+
+    /// Compresses the data with a raw zstd block, which is a valid zstd frame.
+    fn zstd(data: &[u8]) -> Vec<u8> {
+        let mut output = 0xFD2F_B528u32.to_le_bytes().to_vec();
+        // The frame is a single segment, whose size is in the header.
+        if data.len() < 256 {
+            output.extend_from_slice(&[0x20, data.len() as u8]);
+        } else {
+            output.push(0x60);
+            output.extend_from_slice(&((data.len() - 256) as u16).to_le_bytes());
+        }
+        let block_header = 1 | ((data.len() as u32) << 3);
+        output.extend_from_slice(&block_header.to_le_bytes()[..3]);
+        output.extend_from_slice(data);
+        output
+    }
+
+    struct ImageBuilder {
+        image: Vec<u8>,
+        compressor: u16,
+    }
+
+    impl ImageBuilder {
+        fn compress(&self, data: &[u8]) -> Vec<u8> {
+            match self.compressor {
+                COMPRESSOR_ZLIB => zlib(data),
+                _ => zstd(data),
+            }
+        }
+
+        fn push(&mut self, bytes: &[u8]) -> u64 {
+            let offset = self.image.len() as u64;
+            self.image.extend_from_slice(bytes);
+            offset
+        }
+
+        fn push_meta_block(&mut self, data: &[u8], compressed: bool) -> u64 {
+            let (header, data) = if compressed {
+                let data = self.compress(data);
+                (data.len() as u16, data)
+            } else {
+                (data.len() as u16 | META_UNCOMPRESSED, data.to_vec())
+            };
+            let offset = self.push(&header.to_le_bytes());
+            self.push(&data);
+            offset
+        }
+
+        /// Pushes a table in a metadata block, and returns the location of its pointer.
+        fn push_table(&mut self, data: &[u8]) -> u64 {
+            let block = self.push_meta_block(data, true);
+            self.push(&block.to_le_bytes())
+        }
+    }
+
+    fn inode_header(type_: u16, permissions: u16, ino: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&type_.to_le_bytes());
+        header.extend_from_slice(&permissions.to_le_bytes());
+        // The owner is the first ID, and the group is the second ID.
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&MTIME.to_le_bytes());
+        header.extend_from_slice(&ino.to_le_bytes());
+        header
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn dir_listing(entries: &[(&str, u32, u16, u16)]) -> Vec<u8> {
+        let mut listing = u32s(&[entries.len() as u32 - 1, 0, 0]);
+        for &(name, ino, type_, offset) in entries {
+            listing.extend_from_slice(&offset.to_le_bytes());
+            listing.extend_from_slice(&(ino as u16).to_le_bytes());
+            listing.extend_from_slice(&type_.to_le_bytes());
+            listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(name.as_bytes());
+        }
+        listing
+    }
+
+    /// Creates an image with regular files, a sparse file, a symlink, a device, and a
+    /// directory, like `mksquashfs` with `-exports`.
+    fn new_image(compressor: u16) -> Vec<u8> {
+        let mut builder = ImageBuilder {
+            image: vec![0u8; 96],
+            compressor,
+        };
+
+        // The data blocks, which are not compressible.
+        let big = big_data();
+        let big_block = builder.push(&big[..BLOCK_SIZE]);
+        let sparse_first = builder.push(&[0xAA; BLOCK_SIZE]);
+        builder.push(&[0xBB; BLOCK_SIZE]);
+        let fragment_data = [HELLO, NESTED, &big[BLOCK_SIZE..]].concat();
+        let compressed_fragment = builder.compress(&fragment_data);
+        let fragment_block = builder.push(&compressed_fragment);
+
+        // The inodes, where the directories are the last ones.
+        let mut inodes = Vec::new();
+        let mut offsets = [0u16; NUM_INODES as usize + 1];
+        let mut add_inode = |ino: u32, inode: Vec<u8>| {
+            offsets[ino as usize] = inodes.len() as u16;
+            inodes.extend_from_slice(&inode);
+        };
+        let file = |ino: u32, start: u32, fragment_offset: usize, size: usize| {
+            let mut inode = inode_header(2, 0o644, ino);
+            inode.extend_from_slice(&u32s(&[start, 0, fragment_offset as u32, size as u32]));
+            inode
+        };
+        add_inode(HELLO_INO, file(HELLO_INO, 0, 0, HELLO.len()));
+        add_inode(NESTED_INO, file(NESTED_INO, 0, HELLO.len(), NESTED.len()));
+        let mut big_inode = file(
+            BIG_INO,
+            big_block as u32,
+            HELLO.len() + NESTED.len(),
+            BIG_SIZE,
+        );
+        big_inode.extend_from_slice(&u32s(&[BLOCK_SIZE as u32 | DATA_UNCOMPRESSED]));
+        add_inode(BIG_INO, big_inode);
+
+        // The second block of the sparse file is a hole.
+        let mut sparse_inode = inode_header(9, 0o600, SPARSE_INO);
+        sparse_inode.extend_from_slice(&sparse_first.to_le_bytes());
+        sparse_inode.extend_from_slice(&(3 * BLOCK_SIZE as u64).to_le_bytes());
+        sparse_inode.extend_from_slice(&(BLOCK_SIZE as u64).to_le_bytes());
+        sparse_inode.extend_from_slice(&u32s(&[1, NO_FRAGMENT, 0, u32::MAX]));
+        let block = BLOCK_SIZE as u32 | DATA_UNCOMPRESSED;
+        sparse_inode.extend_from_slice(&u32s(&[block, 0, block]));
+        add_inode(SPARSE_INO, sparse_inode);
+
+        let mut link_inode = inode_header(3, 0o777, LINK_INO);
+        link_inode.extend_from_slice(&u32s(&[1, "dir/nested".len() as u32]));
+        link_inode.extend_from_slice(b"dir/nested");
+        add_inode(LINK_INO, link_inode);
+
+        // The device numbers are encoded like Linux's `new_encode_dev`.
+        let mut null_inode = inode_header(5, 0o666, NULL_INO);
+        null_inode.extend_from_slice(&u32s(&[1, (1 << 8) | 3]));
+        add_inode(NULL_INO, null_inode);
+
+        let dir_inode_size = 32;
+        let dir_offset = inodes.len() as u16;
+        let root_offset = dir_offset + dir_inode_size;
+        offsets[DIR_INO as usize] = dir_offset;
+        offsets[ROOT_INO as usize] = root_offset;
+
+        let dir_listing_data =
+            dir_listing(&[("nested", NESTED_INO, 2, offsets[NESTED_INO as usize])]);
+        let root_listing_data = dir_listing(&[
+            ("big", BIG_INO, 2, offsets[BIG_INO as usize]),
+            ("dir", DIR_INO, 1, dir_offset),
+            ("hello", HELLO_INO, 2, offsets[HELLO_INO as usize]),
+            ("link", LINK_INO, 3, offsets[LINK_INO as usize]),
+            ("null", NULL_INO, 5, offsets[NULL_INO as usize]),
+            ("sparse", SPARSE_INO, 2, offsets[SPARSE_INO as usize]),
+        ]);
+        let dir_inode = |ino: u32, offset: usize, size: usize, parent: u32| {
+            let mut inode = inode_header(1, 0o755, ino);
+            inode.extend_from_slice(&u32s(&[0, 2]));
+            inode.extend_from_slice(&((size + 3) as u16).to_le_bytes());
+            inode.extend_from_slice(&(offset as u16).to_le_bytes());
+            inode.extend_from_slice(&parent.to_le_bytes());
+            inode
+        };
+        inodes.extend_from_slice(&dir_inode(DIR_INO, 0, dir_listing_data.len(), ROOT_INO));
+        inodes.extend_from_slice(&dir_inode(
+            ROOT_INO,
+            dir_listing_data.len(),
+            root_listing_data.len(),
+            NUM_INODES + 1,
+        ));
+
+        let inode_table_start = builder.push_meta_block(&inodes, true);
+        let directory_table_start =
+            builder.push_meta_block(&[dir_listing_data, root_listing_data].concat(), true);
+
+        let mut fragment_entry = fragment_block.to_le_bytes().to_vec();
+        fragment_entry.extend_from_slice(&u32s(&[compressed_fragment.len() as u32, 0]));
+        let fragment_table_start = builder.push_table(&fragment_entry);
+        let export_entries: Vec<u8> = (1..=NUM_INODES)
+            .flat_map(|ino| (offsets[ino as usize] as u64).to_le_bytes())
+            .collect();
+        let export_table_start = builder.push_table(&export_entries);
+        let id_table_start = builder.push_table(&u32s(&[1000, 100]));
+        let bytes_used = builder.image.len() as u64;
+
+        let mut super_block = u32s(&[0x7371_7368, NUM_INODES, MTIME, BLOCK_SIZE as u32, 1]);
+        // The export table exists.
+        for value in [compressor, 12, 1 << 7, 2, 4, 0] {
+            super_block.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            root_offset as u64,
+            bytes_used,
+            id_table_start,
+            u64::MAX,
+            inode_table_start,
+            directory_table_start,
+            fragment_table_start,
+            export_table_start,
+        ] {
+            super_block.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut image = builder.image;
+        image[..96].copy_from_slice(&super_block);
+        image.resize(image.len().next_multiple_of(BLOCK_SIZE), 0);
+        image
+    }
+
+    fn open(image: &[u8]) -> Arc<SquashFS> {
+        SquashFS::open(MemoryDisk::from_image(image)).unwrap()
+    }
+
+    fn list(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names = Vec::new();
+        dir.readdir_at(0, &mut names).unwrap();
+        names
+    }
+
+    fn read_all(file: &Arc<dyn Inode>) -> Vec<u8> {
+        let mut buf = vec![0u8; file.size()];
+        file.read_bytes_at(0, &mut buf).unwrap();
+        buf
+    }
+
+    #[ktest]
+    fn read_files() {
+        for compressor in [COMPRESSOR_ZLIB, COMPRESSOR_ZSTD] {
+            let fs = open(&new_image(compressor));
+            let root = fs.root_inode();
+            assert_eq!(
+                list(&root),
+                vec![".", "..", "big", "dir", "hello", "link", "null", "sparse"]
+            );
+
+            let hello = root.lookup("hello").unwrap();
+            assert_eq!(read_all(&hello), HELLO);
+            assert_eq!(hello.ino(), HELLO_INO as u64);
+            assert_eq!(hello.mode().unwrap().bits(), 0o644);
+            assert_eq!(hello.metadata().uid, Uid::new(1000));
+            assert_eq!(hello.metadata().gid, Gid::new(100));
+            assert_eq!(hello.mtime().as_secs(), MTIME as u64);
+
+            assert_eq!(read_all(&root.lookup("big").unwrap()), big_data());
+            let nested = root.lookup("dir").unwrap().lookup("nested").unwrap();
+            assert_eq!(read_all(&nested), NESTED);
+
+            let sparse = read_all(&root.lookup("sparse").unwrap());
+            assert!(sparse[..BLOCK_SIZE].iter().all(|&byte| byte == 0xAA));
+            assert!(sparse[BLOCK_SIZE..2 * BLOCK_SIZE]
+                .iter()
+                .all(|&byte| byte == 0));
+            assert!(sparse[2 * BLOCK_SIZE..].iter().all(|&byte| byte == 0xBB));
+
+            let link = root.lookup("link").unwrap();
+            assert_eq!(link.type_(), InodeType::SymLink);
+            assert_eq!(link.read_link().unwrap(), "dir/nested");
+
+            let null = root.lookup("null").unwrap();
+            assert_eq!(null.type_(), InodeType::CharDevice);
+            assert_eq!(null.metadata().rdev, DeviceId::new(1, 3).into());
+
+            // The volume is read-only.
+            assert_eq!(root.unlink("hello").unwrap_err().error(), Errno::EROFS);
+            assert_eq!(
+                hello.write_bytes_at(0, b"data").unwrap_err().error(),
+                Errno::EROFS
+            );
+            assert_eq!(root.lookup("missing").unwrap_err().error(), Errno::ENOENT);
+        }
+    }
+
+    #[ktest]
+    fn export_table() {
+        let fs = open(&new_image(COMPRESSOR_ZLIB));
+
+        // The inodes are found without their paths, and so are the parents of the directories.
+        let nested = fs.inode_by_ino(NESTED_INO).unwrap();
+        assert_eq!(nested.size(), NESTED.len());
+        let dir = fs.inode_by_ino(DIR_INO).unwrap();
+        assert_eq!(dir.lookup("..").unwrap().ino(), ROOT_INO as u64);
+        assert_eq!(dir.lookup("nested").unwrap().ino(), nested.ino());
+        assert_eq!(fs.root_inode().lookup("dir").unwrap().ino(), DIR_INO as u64);
+        assert_eq!(
+            fs.inode_by_ino(NUM_INODES + 1).unwrap_err().error(),
+            Errno::ESTALE
+        );
+    }
+
+    #[ktest]
+    fn invalid_images() {
+        let mut image = new_image(COMPRESSOR_ZLIB);
+        image[20..22].copy_from_slice(&COMPRESSOR_XZ.to_le_bytes());
+        let disk = MemoryDisk::from_image(&image);
+        assert_eq!(SquashFS::open(disk).unwrap_err().error(), Errno::EINVAL);
+
+        let mut image = new_image(COMPRESSOR_ZLIB);
+        image[0] = 0;
+        let disk = MemoryDisk::from_image(&image);
+        assert_eq!(SquashFS::open(disk).unwrap_err().error(), Errno::EINVAL);
+    }
+}
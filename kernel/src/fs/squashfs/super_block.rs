@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::compressor::Compressor;
+use crate::prelude::*;
+
+/// The magic number of the superblock, which is also reported by `statfs`.
+pub(super) const SQUASHFS_MAGIC: u32 = 0x7371_7368;
+
+/// The minimum and maximum sizes of the data blocks.
+const MIN_BLOCK_SIZE: u32 = 4096;
+const MAX_BLOCK_SIZE: u32 = 1024 * 1024;
+
+bitflags! {
+    /// The flags of the superblock.
+    pub(super) struct SuperBlockFlags: u16 {
+        const UNCOMPRESSED_INODES    = 1 << 0;
+        const UNCOMPRESSED_DATA      = 1 << 1;
+        const CHECK                  = 1 << 2;
+        const UNCOMPRESSED_FRAGMENTS = 1 << 3;
+        const NO_FRAGMENTS           = 1 << 4;
+        const ALWAYS_FRAGMENTS       = 1 << 5;
+        const DUPLICATES             = 1 << 6;
+        /// The export table exists, which maps the inode numbers to the inodes.
+        const EXPORTABLE             = 1 << 7;
+        const UNCOMPRESSED_XATTRS    = 1 << 8;
+        const NO_XATTRS              = 1 << 9;
+        /// The options of the compressor follow the superblock in a metadata block.
+        const COMPRESSOR_OPTIONS     = 1 << 10;
+        const UNCOMPRESSED_IDS       = 1 << 11;
+    }
+}
+
+/// The superblock of SquashFS 4.0, which is at the start of the device.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+pub(super) struct RawSuperBlock {
+    magic: u32,
+    inode_count: u32,
+    mkfs_time: u32,
+    block_size: u32,
+    fragment_count: u32,
+    compressor: u16,
+    block_log: u16,
+    flags: u16,
+    id_count: u16,
+    version_major: u16,
+    version_minor: u16,
+    root_inode: u64,
+    bytes_used: u64,
+    id_table_start: u64,
+    xattr_id_table_start: u64,
+    inode_table_start: u64,
+    directory_table_start: u64,
+    fragment_table_start: u64,
+    export_table_start: u64,
+}
+
+/// The validated superblock, in which all the offsets are in bytes on the device.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct SuperBlock {
+    pub(super) inode_count: u32,
+    pub(super) block_size: usize,
+    pub(super) fragment_count: u32,
+    pub(super) compressor: Compressor,
+    pub(super) flags: SuperBlockFlags,
+    pub(super) id_count: u16,
+    /// The reference to the root inode.
+    pub(super) root_inode: InodeRef,
+    pub(super) bytes_used: u64,
+    pub(super) id_table_start: u64,
+    pub(super) inode_table_start: u64,
+    pub(super) directory_table_start: u64,
+    pub(super) fragment_table_start: u64,
+    /// The start of the export table, which is `None` if the file system is not exportable.
+    pub(super) export_table_start: Option<u64>,
+}
+
+impl TryFrom<RawSuperBlock> for SuperBlock {
+    type Error = Error;
+
+    fn try_from(raw: RawSuperBlock) -> Result<Self> {
+        if raw.magic != SQUASHFS_MAGIC {
+            return_errno_with_message!(Errno::EINVAL, "the SquashFS magic number is invalid");
+        }
+        if raw.version_major != 4 || raw.version_minor != 0 {
+            return_errno_with_message!(Errno::EINVAL, "only SquashFS 4.0 is supported");
+        }
+        let block_size = raw.block_size;
+        if !block_size.is_power_of_two()
+            || !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size)
+            || block_size.trailing_zeros() != raw.block_log as u32
+        {
+            return_errno_with_message!(Errno::EINVAL, "the SquashFS block size is invalid");
+        }
+
+        let flags = SuperBlockFlags::from_bits_truncate(raw.flags);
+        let export_table_start = flags
+            .contains(SuperBlockFlags::EXPORTABLE)
+            .then_some(raw.export_table_start);
+        // The inode table is followed by the directory table, which is followed by the other
+        // tables.
+        if raw.inode_table_start >= raw.directory_table_start
+            || raw.directory_table_start > raw.bytes_used
+        {
+            return_errno_with_message!(Errno::EINVAL, "the SquashFS tables are invalid");
+        }
+
+        Ok(Self {
+            inode_count: raw.inode_count,
+            block_size: block_size as usize,
+            fragment_count: raw.fragment_count,
+            compressor: Compressor::try_from(raw.compressor)?,
+            flags,
+            id_count: raw.id_count,
+            root_inode: InodeRef(raw.root_inode),
+            bytes_used: raw.bytes_used,
+            id_table_start: raw.id_table_start,
+            inode_table_start: raw.inode_table_start,
+            directory_table_start: raw.directory_table_start,
+            fragment_table_start: raw.fragment_table_start,
+            export_table_start,
+        })
+    }
+}
+
+/// A reference to an inode, which is the location of the metadata block relative to the
+/// start of the inode table in the upper 48 bits and the offset within the block in the lower
+/// 16 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) struct InodeRef(pub(super) u64);
+
+impl InodeRef {
+    pub(super) fn new(block: u64, offset: u16) -> Self {
+        Self((block << 16) | offset as u64)
+    }
+
+    pub(super) fn block(&self) -> u64 {
+        self.0 >> 16
+    }
+
+    pub(super) fn offset(&self) -> usize {
+        (self.0 & 0xFFFF) as usize
+    }
+}
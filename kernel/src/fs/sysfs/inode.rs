@@ -4,13 +4,14 @@ use alloc::{
     borrow::Cow,
     string::{String, ToString},
     sync::{Arc, Weak},
+    vec,
     vec::Vec,
 };
 use core::time::Duration;
 
 use aster_systree::{
     SysAttr, SysAttrFlags, SysBranchNode, SysNode, SysNodeId, SysNodeType, SysObj, SysStr,
-    SysSymlink, SysTree,
+    SysSymlink, SysTree, MAX_ATTR_SIZE,
 };
 use ostd::sync::RwLock;
 
@@ -23,7 +24,7 @@ use crate::{
             MknodType,
         },
     },
-    prelude::{VmReader, VmWriter},
+    prelude::{FallibleVmWrite, VmReader, VmWriter},
     process::{signal::PollHandle, Gid, Uid},
     return_errno_with_message,
     time::{clocks::RealTimeCoarseClock, Clock},
@@ -373,16 +374,25 @@ impl Inode for SysFsInode {
         self.read_direct_at(offset, buf)
     }
 
-    fn read_direct_at(&self, _offset: usize, buf: &mut VmWriter) -> Result<usize> {
-        // TODO: it is unclear whether we should simply ignore the offset
-        // or report errors if it is non-zero.
-
+    fn read_direct_at(&self, offset: usize, buf: &mut VmWriter) -> Result<usize> {
         let InnerNode::Attr(attr, leaf) = &self.inner_node else {
             return Err(Error::new(Errno::EINVAL));
         };
 
         // TODO: check read permission
-        Ok(leaf.read_attr(attr.name(), buf)?)
+        if offset == 0 {
+            return Ok(leaf.read_attr(attr.name(), buf)?);
+        }
+
+        // Like Linux, the whole value is read and then sliced,
+        // so that readers like `cat` see the end of the file.
+        let mut value = vec![0u8; MAX_ATTR_SIZE];
+        let mut writer = VmWriter::from(value.as_mut_slice()).to_fallible();
+        let len = leaf.read_attr(attr.name(), &mut writer)?;
+        if offset >= len {
+            return Ok(0);
+        }
+        Ok(buf.write_fallible(&mut (&value[offset..len]).into())?)
     }
 
     fn write_at(&self, offset: usize, buf: &mut VmReader) -> Result<usize> {
@@ -153,6 +153,68 @@ impl Producer<u8> {
             return_errno_with_message!(Errno::EAGAIN, "the channel is full");
         }
     }
+
+    /// Tries to write all bytes in `reader` to the channel at once.
+    ///
+    /// Unlike [`Self::try_write`], the bytes are never partially written, regardless of how many
+    /// bytes there are.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes written if successful.
+    /// - Returns `Err(EPIPE)` if the channel is shut down.
+    /// - Returns `Err(EMSGSIZE)` if the bytes can never fit in the channel.
+    /// - Returns `Err(EAGAIN)` if the channel does not have enough free space.
+    pub fn try_write_all(&self, reader: &mut dyn MultiRead) -> Result<usize> {
+        if reader.is_empty() {
+            return Ok(0);
+        }
+
+        if self.is_shutdown() {
+            return_errno_with_message!(Errno::EPIPE, "the channel is shut down");
+        }
+
+        if reader.sum_lens() > self.0.common.capacity() {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        let written_len = self.0.write_all(reader)?;
+        if written_len == 0 {
+            return_errno_with_message!(Errno::EAGAIN, "the channel does not have enough space");
+        }
+        self.peer_end().pollee.notify(IoEvents::IN);
+
+        Ok(written_len)
+    }
+
+    /// Tries to write at most `max_len` bytes to the channel with `write_fn`.
+    ///
+    /// `write_fn` is called with writers to the free space of the channel, and returns the
+    /// number of bytes written, which is zero at the end of the data source.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes written if successful.
+    /// - Returns `Err(EPIPE)` if the channel is shut down.
+    /// - Returns `Err(EAGAIN)` if the channel is full.
+    pub fn try_write_with(
+        &self,
+        max_len: usize,
+        write_fn: &mut dyn FnMut(&mut VmWriter) -> Result<usize>,
+    ) -> Result<usize> {
+        if max_len == 0 {
+            return Ok(0);
+        }
+
+        if self.is_shutdown() {
+            return_errno_with_message!(Errno::EPIPE, "the channel is shut down");
+        }
+
+        let Some(written_len) = self.0.write_with(max_len, write_fn)? else {
+            return_errno_with_message!(Errno::EAGAIN, "the channel is full");
+        };
+        if written_len > 0 {
+            self.peer_end().pollee.notify(IoEvents::IN);
+        }
+
+        Ok(written_len)
+    }
 }
 
 impl<T: Pod> Producer<T> {
@@ -235,6 +297,61 @@ impl Consumer<u8> {
             return_errno_with_message!(Errno::EAGAIN, "the channel is empty");
         }
     }
+
+    /// Tries to read at most `max_len` bytes from the channel with `read_fn`.
+    ///
+    /// `read_fn` is called with readers of the data in the channel, and returns the number of
+    /// bytes read.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes read if successful.
+    /// - Returns `Ok(0)` if the channel is shut down and there is no data left.
+    /// - Returns `Err(EAGAIN)` if the channel is empty.
+    pub fn try_read_with(
+        &self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        self.try_read_with_inner(max_len, read_fn, true)
+    }
+
+    /// Tries to read at most `max_len` bytes from the channel with `read_fn`, without
+    /// consuming them.
+    ///
+    /// The return value is the same as [`Self::try_read_with`].
+    pub fn try_peek_with(
+        &self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        self.try_read_with_inner(max_len, read_fn, false)
+    }
+
+    fn try_read_with_inner(
+        &self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+        consume: bool,
+    ) -> Result<usize> {
+        if max_len == 0 {
+            return Ok(0);
+        }
+
+        // This must be recorded before the actual operation to avoid race conditions.
+        let is_shutdown = self.is_shutdown();
+
+        let Some(read_len) = self.0.read_with(max_len, read_fn, consume)? else {
+            if is_shutdown {
+                return Ok(0);
+            }
+            return_errno_with_message!(Errno::EAGAIN, "the channel is empty");
+        };
+        if consume {
+            self.peer_end().pollee.notify(IoEvents::OUT);
+            self.this_end().pollee.invalidate();
+        }
+
+        Ok(read_len)
+    }
 }
 
 impl<T: Pod> Consumer<T> {
@@ -297,6 +414,55 @@ impl<R: TRights> Fifo<u8, R> {
         }
         rb.write_fallible(reader)
     }
+
+    /// Writes all bytes in `reader` to the endpoint at once.
+    ///
+    /// Returns `Ok(0)` if the endpoint does not have enough free space.
+    #[require(R > Write)]
+    pub fn write_all(&self, reader: &mut dyn MultiRead) -> Result<usize> {
+        let mut rb = self.common.producer.rb();
+        if rb.free_len() < reader.sum_lens() {
+            return Ok(0);
+        }
+        rb.write_fallible(reader)
+    }
+
+    /// Writes to the endpoint with `write_fn`.
+    ///
+    /// Returns `None` if the endpoint is full.
+    #[require(R > Write)]
+    pub fn write_with(
+        &self,
+        max_len: usize,
+        write_fn: &mut dyn FnMut(&mut VmWriter) -> Result<usize>,
+    ) -> Result<Option<usize>> {
+        let mut rb = self.common.producer.rb();
+        if rb.free_len() == 0 {
+            return Ok(None);
+        }
+        rb.write_with(max_len, write_fn).map(Some)
+    }
+
+    /// Reads from the endpoint with `read_fn`, consuming the data if `consume` is true.
+    ///
+    /// Returns `None` if the endpoint is empty.
+    #[require(R > Read)]
+    pub fn read_with(
+        &self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+        consume: bool,
+    ) -> Result<Option<usize>> {
+        let mut rb = self.common.consumer.rb();
+        if rb.is_empty() {
+            return Ok(None);
+        }
+        if consume {
+            rb.read_with(max_len, read_fn).map(Some)
+        } else {
+            rb.peek_with(max_len, read_fn).map(Some)
+        }
+    }
 }
 
 impl<T: Pod, R: TRights> Fifo<T, R> {
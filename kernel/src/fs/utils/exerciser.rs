@@ -0,0 +1,536 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A file system exerciser for ktests.
+//!
+//! The exerciser performs random operations (creating, writing, renaming, truncating, and removing
+//! files) in a directory of any file system and checks the results against a model, which records
+//! the expected contents. The contents are compared by their checksums.
+//!
+//! It also provides crash-consistency checks for the file systems on block devices and
+//! micro-benchmarks for the metadata operations and the streaming I/O. A new file system should
+//! be validated by running all of them on its root directory.
+
+use core::time::Duration;
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+use ostd::mm::{FrameAllocOptions, Segment, VmIo, PAGE_SIZE};
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+
+use super::{FileSystem, Inode, InodeMode, InodeType};
+use crate::{
+    prelude::*,
+    time::{clocks::MonotonicClock, Clock},
+};
+
+/// The directory where the exerciser works, which is created in the exercised directory.
+const SCRATCH_DIR_NAME: &str = "fs_exerciser";
+/// The maximum length of a write.
+const MAX_WRITE_LEN: usize = 2 * PAGE_SIZE;
+
+const NR_BENCH_FILES: usize = 256;
+const BENCH_FILE_SIZE: usize = 4 * 1024 * 1024;
+const BENCH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An exerciser that performs random operations in a directory.
+pub struct FsExerciser {
+    dir: Arc<dyn Inode>,
+    model: FsModel,
+    rng: SmallRng,
+    next_id: u32,
+}
+
+/// The expected contents of the directory that the exerciser works in.
+#[derive(Clone, Default)]
+pub struct FsModel {
+    /// The files and the directories, indexed by their paths relative to the directory.
+    entries: BTreeMap<String, ModelEntry>,
+}
+
+#[derive(Clone)]
+enum ModelEntry {
+    File(Vec<u8>),
+    Dir,
+}
+
+impl FsExerciser {
+    /// Creates an exerciser that works in a new directory under `parent`.
+    ///
+    /// The random operations are determined by `seed`, so a failure can be reproduced.
+    pub fn new(parent: &Arc<dyn Inode>, seed: u64) -> Self {
+        let dir = parent
+            .create(
+                SCRATCH_DIR_NAME,
+                InodeType::Dir,
+                InodeMode::from_bits_truncate(0o755),
+            )
+            .unwrap();
+
+        Self {
+            dir,
+            model: FsModel::default(),
+            rng: SmallRng::seed_from_u64(seed),
+            next_id: 0,
+        }
+    }
+
+    /// Returns the expected contents of the directory.
+    pub fn model(&self) -> &FsModel {
+        &self.model
+    }
+
+    /// Performs `nr_ops` random operations and verifies the contents of the directory.
+    pub fn run(&mut self, nr_ops: usize) {
+        for _ in 0..nr_ops {
+            match self.rng.gen_range(0..6) {
+                0 => self.create(InodeType::File),
+                1 => self.create(InodeType::Dir),
+                2 => self.write(),
+                3 => self.truncate(),
+                4 => self.rename(),
+                _ => self.remove(),
+            }
+        }
+
+        self.model.verify(&self.dir);
+    }
+
+    fn create(&mut self, type_: InodeType) {
+        let parent_path = self.random_dir();
+        let path = self.new_path(&parent_path);
+        debug!("create {:?} ({:?})", path, type_);
+
+        let mode = match type_ {
+            InodeType::Dir => InodeMode::from_bits_truncate(0o755),
+            _ => InodeMode::from_bits_truncate(0o644),
+        };
+        lookup_path(&self.dir, &parent_path)
+            .create(file_name(&path), type_, mode)
+            .unwrap_or_else(|err| panic!("failed to create {:?}: {:?}", path, err));
+
+        let entry = match type_ {
+            InodeType::Dir => ModelEntry::Dir,
+            _ => ModelEntry::File(Vec::new()),
+        };
+        self.model.entries.insert(path, entry);
+    }
+
+    fn write(&mut self) {
+        let Some(path) = self.random_file() else {
+            return;
+        };
+        let Some(ModelEntry::File(contents)) = self.model.entries.get_mut(&path) else {
+            unreachable!();
+        };
+
+        // Writing beyond the end of the file is avoided, since some file systems do not support
+        // holes in files.
+        let offset = self.rng.gen_range(0..=contents.len());
+        let mut buf = vec![0; self.rng.gen_range(1..=MAX_WRITE_LEN)];
+        self.rng.fill_bytes(&mut buf);
+        debug!("write {:?}: offset = {}, len = {}", path, offset, buf.len());
+
+        let written_len = lookup_path(&self.dir, &path)
+            .write_bytes_at(offset, &buf)
+            .unwrap_or_else(|err| panic!("failed to write {:?}: {:?}", path, err));
+        assert_eq!(written_len, buf.len(), "short write to {:?}", path);
+
+        let end = offset + buf.len();
+        if end > contents.len() {
+            contents.resize(end, 0);
+        }
+        contents[offset..end].copy_from_slice(&buf);
+    }
+
+    fn truncate(&mut self) {
+        let Some(path) = self.random_file() else {
+            return;
+        };
+        let Some(ModelEntry::File(contents)) = self.model.entries.get_mut(&path) else {
+            unreachable!();
+        };
+
+        let new_size = self.rng.gen_range(0..=contents.len());
+        debug!("truncate {:?}: new_size = {}", path, new_size);
+
+        lookup_path(&self.dir, &path)
+            .resize(new_size)
+            .unwrap_or_else(|err| panic!("failed to truncate {:?}: {:?}", path, err));
+        contents.truncate(new_size);
+    }
+
+    fn rename(&mut self) {
+        let Some(old_path) = self.random_entry(|_| true) else {
+            return;
+        };
+        let is_dir = matches!(self.model.entries[&old_path], ModelEntry::Dir);
+
+        // A directory cannot be moved into itself.
+        let new_parent_path = self.random_dir();
+        if is_dir && is_same_or_descendant(&new_parent_path, &old_path) {
+            return;
+        }
+
+        // A file may replace another file, while a directory is always moved to a new name.
+        let new_path = match self.random_file() {
+            Some(path) if !is_dir && path != old_path && self.rng.gen_bool(0.5) => path,
+            _ => self.new_path(&new_parent_path),
+        };
+        debug!("rename {:?} to {:?}", old_path, new_path);
+
+        let old_parent = lookup_path(&self.dir, parent_path(&old_path));
+        let new_parent = lookup_path(&self.dir, parent_path(&new_path));
+        old_parent
+            .rename(file_name(&old_path), &new_parent, file_name(&new_path))
+            .unwrap_or_else(|err| {
+                panic!(
+                    "failed to rename {:?} to {:?}: {:?}",
+                    old_path, new_path, err
+                )
+            });
+
+        let moved_paths: Vec<String> = self
+            .model
+            .entries
+            .keys()
+            .filter(|path| is_same_or_descendant(path, &old_path))
+            .cloned()
+            .collect();
+        for path in moved_paths {
+            let entry = self.model.entries.remove(&path).unwrap();
+            let path = format!("{}{}", new_path, &path[old_path.len()..]);
+            self.model.entries.insert(path, entry);
+        }
+    }
+
+    fn remove(&mut self) {
+        let Some(path) = self.random_entry(|_| true) else {
+            return;
+        };
+        let parent = lookup_path(&self.dir, parent_path(&path));
+
+        if let ModelEntry::File(_) = self.model.entries[&path] {
+            debug!("unlink {:?}", path);
+            parent
+                .unlink(file_name(&path))
+                .unwrap_or_else(|err| panic!("failed to unlink {:?}: {:?}", path, err));
+        } else {
+            let is_empty = !self
+                .model
+                .entries
+                .keys()
+                .any(|other| other != &path && is_same_or_descendant(other, &path));
+            debug!("rmdir {:?}: is_empty = {}", path, is_empty);
+
+            let res = parent.rmdir(file_name(&path));
+            if !is_empty {
+                assert!(res.is_err(), "removed the non-empty directory {:?}", path);
+                return;
+            }
+            res.unwrap_or_else(|err| panic!("failed to rmdir {:?}: {:?}", path, err));
+        }
+
+        self.model.entries.remove(&path);
+    }
+
+    /// Returns the path of a random directory, where the empty path means the scratch directory.
+    fn random_dir(&mut self) -> String {
+        if self.rng.gen_bool(0.5) {
+            return String::new();
+        }
+        self.random_entry(|entry| matches!(entry, ModelEntry::Dir))
+            .unwrap_or_default()
+    }
+
+    fn random_file(&mut self) -> Option<String> {
+        self.random_entry(|entry| matches!(entry, ModelEntry::File(_)))
+    }
+
+    fn random_entry(&mut self, filter: impl Fn(&ModelEntry) -> bool) -> Option<String> {
+        let paths: Vec<&String> = self
+            .model
+            .entries
+            .iter()
+            .filter(|(_, entry)| filter(entry))
+            .map(|(path, _)| path)
+            .collect();
+        if paths.is_empty() {
+            return None;
+        }
+        Some(paths[self.rng.gen_range(0..paths.len())].clone())
+    }
+
+    fn new_path(&mut self, parent_path: &str) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        if parent_path.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}/{}", parent_path, id)
+        }
+    }
+}
+
+impl FsModel {
+    /// Verifies the contents of `dir` against the model.
+    pub fn verify(&self, dir: &Arc<dyn Inode>) {
+        self.verify_children(dir, "");
+
+        for (path, entry) in self.entries.iter() {
+            let inode = lookup_path(dir, path);
+            match entry {
+                ModelEntry::File(contents) => {
+                    assert_eq!(inode.type_(), InodeType::File, "{:?} is not a file", path);
+                    assert_eq!(inode.size(), contents.len(), "size mismatch of {:?}", path);
+
+                    let mut buf = vec![0; contents.len()];
+                    inode
+                        .read_bytes_at(0, &mut buf)
+                        .unwrap_or_else(|err| panic!("failed to read {:?}: {:?}", path, err));
+                    let (expected, found) = (checksum(contents), checksum(&buf));
+                    assert_eq!(
+                        expected, found,
+                        "checksum mismatch of {:?}: expected {:#x}, found {:#x}",
+                        path, expected, found
+                    );
+                }
+                ModelEntry::Dir => {
+                    assert_eq!(
+                        inode.type_(),
+                        InodeType::Dir,
+                        "{:?} is not a directory",
+                        path
+                    );
+                    self.verify_children(&inode, path);
+                }
+            }
+        }
+    }
+
+    fn verify_children(&self, dir_inode: &Arc<dyn Inode>, dir_path: &str) {
+        let mut names = Vec::new();
+        dir_inode
+            .readdir_at(0, &mut names)
+            .unwrap_or_else(|err| panic!("failed to read the directory {:?}: {:?}", dir_path, err));
+        names.retain(|name| name != "." && name != "..");
+        names.sort();
+
+        let mut expected_names: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|path| parent_path(path) == dir_path)
+            .map(|path| file_name(path).to_string())
+            .collect();
+        expected_names.sort();
+
+        assert_eq!(
+            names, expected_names,
+            "entries mismatch of the directory {:?}",
+            dir_path
+        );
+    }
+}
+
+/// Checks that a file system recovers the synced contents after a crash.
+///
+/// The file system is opened on `disk` by `open_fs`. After some random operations, the file
+/// system is synced, and the disk at this moment is kept. Then more operations are performed
+/// without syncing, which are lost in the simulated crash. The file system that is reopened on
+/// the kept disk must contain exactly the synced contents.
+pub fn check_crash_consistency(
+    disk: Arc<MemoryDisk>,
+    open_fs: impl Fn(Arc<dyn BlockDevice>) -> Arc<dyn FileSystem>,
+    seed: u64,
+    nr_ops: usize,
+) {
+    let fs = open_fs(disk.clone());
+    let mut exerciser = FsExerciser::new(&fs.root_inode(), seed);
+    exerciser.run(nr_ops);
+
+    fs.sync().unwrap();
+    let synced_model = exerciser.model().clone();
+    let crashed_disk = disk.snapshot();
+
+    exerciser.run(nr_ops);
+    drop(exerciser);
+    drop(fs);
+
+    let fs = open_fs(crashed_disk);
+    let dir = fs
+        .root_inode()
+        .lookup(SCRATCH_DIR_NAME)
+        .expect("the synced directory is lost after the crash");
+    synced_model.verify(&dir);
+}
+
+/// The results of the micro-benchmarks.
+#[derive(Debug)]
+pub struct FsBenchResult {
+    /// The number of the creating, looking up, and unlinking operations per second.
+    pub metadata_ops_per_sec: u64,
+    /// The throughput of the sequential writes in bytes per second.
+    pub write_bytes_per_sec: u64,
+    /// The throughput of the sequential reads in bytes per second.
+    pub read_bytes_per_sec: u64,
+}
+
+/// Runs the micro-benchmarks in a new directory under `parent`.
+pub fn run_benchmarks(parent: &Arc<dyn Inode>) -> FsBenchResult {
+    let dir = parent
+        .create(
+            SCRATCH_DIR_NAME,
+            InodeType::Dir,
+            InodeMode::from_bits_truncate(0o755),
+        )
+        .unwrap();
+    let file_mode = InodeMode::from_bits_truncate(0o644);
+
+    let names: Vec<String> = (0..NR_BENCH_FILES).map(|i| i.to_string()).collect();
+    let metadata_time = measure(|| {
+        for name in names.iter() {
+            dir.create(name, InodeType::File, file_mode).unwrap();
+        }
+        for name in names.iter() {
+            dir.lookup(name).unwrap();
+        }
+        for name in names.iter() {
+            dir.unlink(name).unwrap();
+        }
+    });
+
+    let file = dir.create("stream", InodeType::File, file_mode).unwrap();
+    let mut buf = vec![0xa5; BENCH_CHUNK_SIZE];
+    let write_time = measure(|| {
+        for offset in (0..BENCH_FILE_SIZE).step_by(BENCH_CHUNK_SIZE) {
+            file.write_bytes_at(offset, &buf).unwrap();
+        }
+    });
+    let read_time = measure(|| {
+        for offset in (0..BENCH_FILE_SIZE).step_by(BENCH_CHUNK_SIZE) {
+            file.read_bytes_at(offset, &mut buf).unwrap();
+        }
+    });
+    dir.unlink("stream").unwrap();
+    parent.rmdir(SCRATCH_DIR_NAME).unwrap();
+
+    let result = FsBenchResult {
+        metadata_ops_per_sec: per_sec(3 * NR_BENCH_FILES, metadata_time),
+        write_bytes_per_sec: per_sec(BENCH_FILE_SIZE, write_time),
+        read_bytes_per_sec: per_sec(BENCH_FILE_SIZE, read_time),
+    };
+    info!("File system benchmarks: {:?}", result);
+    result
+}
+
+fn measure(f: impl FnOnce()) -> Duration {
+    let start = MonotonicClock::get().read_time();
+    f();
+    MonotonicClock::get().read_time() - start
+}
+
+fn per_sec(count: usize, time: Duration) -> u64 {
+    (count as u128 * 1_000_000_000 / time.as_nanos().max(1)) as u64
+}
+
+/// A block device in memory, whose contents can be kept to simulate crashes.
+pub struct MemoryDisk {
+    segment: Segment<()>,
+}
+
+impl MemoryDisk {
+    /// Creates a disk with the contents of a disk image.
+    pub fn from_image(image: &[u8]) -> Arc<Self> {
+        let segment = FrameAllocOptions::new()
+            .zeroed(false)
+            .alloc_segment(image.len().div_ceil(PAGE_SIZE))
+            .unwrap();
+        segment.write_bytes(0, image).unwrap();
+
+        Arc::new(Self { segment })
+    }
+
+    /// Creates a disk with the current contents of the disk.
+    ///
+    /// The later writes to the disk are not seen by the new disk, as if they were lost in a
+    /// crash.
+    pub fn snapshot(&self) -> Arc<Self> {
+        let segment = FrameAllocOptions::new()
+            .zeroed(false)
+            .alloc_segment(self.segment.size() / PAGE_SIZE)
+            .unwrap();
+        segment.writer().write(&mut self.segment.reader());
+
+        Arc::new(Self { segment })
+    }
+}
+
+impl Debug for MemoryDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("MemoryDisk")
+            .field("size", &self.segment.size())
+            .finish()
+    }
+}
+
+impl BlockDevice for MemoryDisk {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        let mut offset = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+        for seg in bio.segments() {
+            let len = match bio.type_() {
+                BioType::Read => seg
+                    .inner_segment()
+                    .writer()
+                    .write(&mut self.segment.reader().skip(offset)),
+                BioType::Write => self
+                    .segment
+                    .writer()
+                    .skip(offset)
+                    .write(&mut seg.inner_segment().reader()),
+                _ => 0,
+            };
+            offset += len;
+        }
+        bio.complete(BioStatus::Complete);
+        Ok(())
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: usize::MAX,
+            nr_sectors: self.segment.size() / SECTOR_SIZE,
+        }
+    }
+}
+
+fn lookup_path(dir: &Arc<dyn Inode>, path: &str) -> Arc<dyn Inode> {
+    let mut inode = dir.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        inode = inode
+            .lookup(name)
+            .unwrap_or_else(|err| panic!("failed to look up {:?}: {:?}", path, err));
+    }
+    inode
+}
+
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
+
+fn is_same_or_descendant(path: &str, ancestor: &str) -> bool {
+    path.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Computes the 64-bit FNV-1a hash of the data.
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -138,11 +138,25 @@ impl FlockList {
     /// If no conflicting locks exist, the lock is set and the function returns `Ok(())`.
     /// If is_nonblocking is true and a conflicting lock exists, the function returns `EAGAIN`.
     /// Otherwise, the function waits until the lock can be acquired or until it is interrupted by a signal.
+    ///
+    /// If the owner already holds a lock of another type, the existing lock is released before
+    /// the new lock is acquired. Like Linux, the conversion is not atomic, so that two owners
+    /// converting their shared locks to exclusive locks do not deadlock.
     pub fn set_lock(&self, req_lock: FlockItem, is_nonblocking: bool) -> Result<()> {
         debug!(
             "set_lock with Flock: {:?}, is_nonblocking: {}",
             req_lock, is_nonblocking
         );
+        {
+            let mut list = self.inner.lock();
+            if let Some(idx) = list.iter().position(|l| req_lock.same_owner_with(l)) {
+                if list[idx].lock.type_ == req_lock.lock.type_ {
+                    return Ok(());
+                }
+                // Dropping the existing lock wakes the threads waiting for it.
+                list.remove(idx);
+            }
+        }
         if is_nonblocking {
             self.try_set_lock(&req_lock, None)
         } else {
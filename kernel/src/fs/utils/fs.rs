@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::Inode;
-use crate::prelude::*;
+use crate::{fs::quota::Quota, prelude::*};
 
 #[derive(Debug, Clone)]
 pub struct SuperBlock {
@@ -51,6 +51,11 @@ pub trait FileSystem: Any + Sync + Send {
     fn sb(&self) -> SuperBlock;
 
     fn flags(&self) -> FsFlags;
+
+    /// Returns the disk quotas, or `None` if the file system does not support quotas.
+    fn quota(&self) -> Option<&Quota> {
+        None
+    }
 }
 
 impl dyn FileSystem {
@@ -2,11 +2,11 @@
 
 #![expect(unused_variables)]
 
-use core::{any::TypeId, time::Duration};
+use core::{any::TypeId, ops::Range, time::Duration};
 
 use aster_rights::Full;
 use core2::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
-use ostd::task::Task;
+use ostd::{mm::UntypedMem, task::Task};
 
 use super::{
     AccessMode, DirentVisitor, FallocMode, FileSystem, IoctlCmd, XattrName, XattrNamespace,
@@ -14,11 +14,14 @@ use super::{
 };
 use crate::{
     events::IoEvents,
-    fs::device::{Device, DeviceType},
+    fs::{
+        device::{Device, DeviceType},
+        inode_handle::FileIo,
+    },
     prelude::*,
     process::{posix_thread::AsPosixThread, signal::PollHandle, Gid, Uid},
     time::clocks::RealTimeCoarseClock,
-    vm::vmo::Vmo,
+    vm::vmo::{CommitFlags, Vmo},
 };
 
 #[repr(u16)]
@@ -317,6 +320,105 @@ impl Metadata {
             rdev: 0,
         }
     }
+
+    /// Checks the read/write/execute permissions of the current thread on the file.
+    ///
+    /// Similar to Linux, using "fsuid" here allows setting filesystem permissions
+    /// without changing the "normal" uids for other tasks.
+    pub fn check_permission(&self, mut perm: Permission) -> Result<()> {
+        let creds = match Task::current() {
+            Some(task) => match task.as_posix_thread() {
+                Some(thread) => thread.credentials(),
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        perm =
+            perm.intersection(Permission::MAY_READ | Permission::MAY_WRITE | Permission::MAY_EXEC);
+        let mode = self.mode;
+
+        if self.uid == creds.fsuid() {
+            if (perm.may_read() && !mode.is_owner_readable())
+                || (perm.may_write() && !mode.is_owner_writable())
+                || (perm.may_exec() && !mode.is_owner_executable())
+            {
+                return_errno_with_message!(Errno::EACCES, "owner permission check failed");
+            }
+        } else if self.gid == creds.fsgid() {
+            if (perm.may_read() && !mode.is_group_readable())
+                || (perm.may_write() && !mode.is_group_writable())
+                || (perm.may_exec() && !mode.is_group_executable())
+            {
+                return_errno_with_message!(Errno::EACCES, "group permission check failed");
+            }
+        } else if (perm.may_read() && !mode.is_other_readable())
+            || (perm.may_write() && !mode.is_other_writable())
+            || (perm.may_exec() && !mode.is_other_executable())
+        {
+            return_errno_with_message!(Errno::EACCES, "other permission check failed");
+        }
+
+        Ok(())
+    }
+}
+
+/// The metadata of an inode that is not included in [`Metadata`].
+///
+/// The metadata is queried with [`Inode::extended_metadata`]. File systems fill in the
+/// fields that they support and leave the others as default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtendedMetadata {
+    /// The creation (birth) time, if the file system records it.
+    pub btime: Option<Duration>,
+    /// The attributes of the file.
+    pub attributes: FileAttributes,
+    /// The attributes that are supported by the file system.
+    ///
+    /// An attribute not in the mask is unknown rather than unset.
+    pub attributes_mask: FileAttributes,
+}
+
+bitflags! {
+    /// The attributes of a file.
+    ///
+    /// The values are the same as Linux's `STATX_ATTR_*` flags.
+    #[derive(Default)]
+    pub struct FileAttributes: u64 {
+        /// The file is compressed by the file system.
+        const COMPRESSED = 0x0000_0004;
+        /// The file cannot be modified.
+        const IMMUTABLE = 0x0000_0010;
+        /// The file can only be opened in append mode for writing.
+        const APPEND = 0x0000_0020;
+        /// The file is not a candidate for backup.
+        const NODUMP = 0x0000_0040;
+        /// The file requires a key to be encrypted by the file system.
+        const ENCRYPTED = 0x0000_0800;
+        /// The directory is an automount trigger.
+        const AUTOMOUNT = 0x0000_1000;
+        /// The directory is the root of a mount.
+        const MOUNT_ROOT = 0x0000_2000;
+        /// The file has fs-verity enabled.
+        const VERITY = 0x0010_0000;
+        /// The file is in the DAX (CPU direct access) state.
+        const DAX = 0x0020_0000;
+    }
+}
+
+bitflags! {
+    /// The flags of an inode, which are changed by `chattr`.
+    ///
+    /// The values are the same as Linux's `FS_*_FL` flags. Only the flags enforced by the VFS
+    /// are defined.
+    #[derive(Default)]
+    pub struct InodeFlags: u32 {
+        /// The file cannot be modified, deleted, renamed, or linked to.
+        const IMMUTABLE = 0x0000_0010;
+        /// The file can only be opened in append mode for writing, and cannot be deleted,
+        /// renamed, or linked to.
+        const APPEND = 0x0000_0020;
+    }
 }
 
 pub enum MknodType {
@@ -353,6 +455,23 @@ pub trait Inode: Any + Sync + Send {
 
     fn metadata(&self) -> Metadata;
 
+    /// Returns the metadata that is not included in [`Metadata`], e.g., the birth time.
+    fn extended_metadata(&self) -> ExtendedMetadata {
+        ExtendedMetadata::default()
+    }
+
+    /// Returns the flags of the inode, which are changed by `chattr`.
+    fn flags(&self) -> InodeFlags {
+        InodeFlags::empty()
+    }
+
+    /// Sets the flags of the inode.
+    ///
+    /// The permission is checked by the caller.
+    fn set_flags(&self, flags: InodeFlags) -> Result<()> {
+        return_errno_with_message!(Errno::ENOTTY, "the inode flags are not supported");
+    }
+
     fn ino(&self) -> u64;
 
     fn type_(&self) -> InodeType;
@@ -413,6 +532,14 @@ pub trait Inode: Any + Sync + Send {
         None
     }
 
+    /// Opens the inode, returning the I/O object of the opened file if the inode
+    /// keeps states for each opened file.
+    ///
+    /// Device inodes are opened by [`Device::open`] instead.
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(None)
+    }
+
     fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
         Err(Error::new(Errno::ENOTDIR))
     }
@@ -437,6 +564,21 @@ pub trait Inode: Any + Sync + Send {
         Err(Error::new(Errno::ENOTDIR))
     }
 
+    /// Creates a symbolic link named `name` that points to `target`.
+    ///
+    /// By default, the link is created with [`Inode::create`] and then filled with
+    /// [`Inode::write_link`]. File systems that cannot create an empty link (e.g., FUSE)
+    /// should override this method to create the link in one step.
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>> {
+        let inode = self.create(
+            name,
+            InodeType::SymLink,
+            InodeMode::from_bits_truncate(0o777),
+        )?;
+        inode.write_link(target)?;
+        Ok(inode)
+    }
+
     fn read_link(&self) -> Result<String> {
         Err(Error::new(Errno::EISDIR))
     }
@@ -445,6 +587,14 @@ pub trait Inode: Any + Sync + Send {
         Err(Error::new(Errno::EISDIR))
     }
 
+    /// Returns whether the inode is a magic link.
+    ///
+    /// A magic link (e.g., `/proc/self/fd/0`) is a symlink that stands for an opened file
+    /// or a directory of a process, rather than a path stored in the file system.
+    fn is_magic_link(&self) -> bool {
+        false
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         Err(Error::new(Errno::EISDIR))
     }
@@ -463,6 +613,38 @@ pub trait Inode: Any + Sync + Send {
         return_errno!(Errno::EOPNOTSUPP);
     }
 
+    /// Returns the first data extent of the file that ends after `offset`.
+    ///
+    /// The returned extent may start before `offset`, and `None` means that there is no data
+    /// after `offset`. File systems supporting sparse files should implement this method to
+    /// report their holes. By default, the whole file is treated as a single data extent, which
+    /// is how Linux handles the file systems without the support for holes.
+    fn next_data_extent(&self, offset: usize) -> Result<Option<Range<usize>>> {
+        let size = self.size();
+        if offset >= size {
+            return Ok(None);
+        }
+        Ok(Some(0..size))
+    }
+
+    /// Copies at most `len` bytes from `src` at `src_offset` to this inode at `offset`.
+    ///
+    /// File systems can implement this method to copy faster than the generic copy via the
+    /// page cache, e.g., by sharing the data blocks (reflinks) or by copying on the server side.
+    /// `src` is a regular file that may belong to another file system. If the copy cannot be
+    /// accelerated, this method returns `EOPNOTSUPP`. See also [`copy_from`].
+    ///
+    /// [`copy_from`]: <dyn Inode>::copy_from
+    fn copy_range_from(
+        &self,
+        offset: usize,
+        src: &dyn Inode,
+        src_offset: usize,
+        len: usize,
+    ) -> Result<usize> {
+        return_errno!(Errno::EOPNOTSUPP);
+    }
+
     fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
         let events = IoEvents::IN | IoEvents::OUT;
         events & mask
@@ -522,45 +704,8 @@ pub trait Inode: Any + Sync + Send {
     }
 
     /// Used to check for read/write/execute permissions on a file.
-    ///
-    /// Similar to Linux, using "fsuid" here allows setting filesystem permissions
-    /// without changing the "normal" uids for other tasks.
-    fn check_permission(&self, mut perm: Permission) -> Result<()> {
-        let creds = match Task::current() {
-            Some(task) => match task.as_posix_thread() {
-                Some(thread) => thread.credentials(),
-                None => return Ok(()),
-            },
-            None => return Ok(()),
-        };
-
-        perm =
-            perm.intersection(Permission::MAY_READ | Permission::MAY_WRITE | Permission::MAY_EXEC);
-        let metadata = self.metadata();
-        let mode = metadata.mode;
-
-        if metadata.uid == creds.fsuid() {
-            if (perm.may_read() && !mode.is_owner_readable())
-                || (perm.may_write() && !mode.is_owner_writable())
-                || (perm.may_exec() && !mode.is_owner_executable())
-            {
-                return_errno_with_message!(Errno::EACCES, "owner permission check failed");
-            }
-        } else if metadata.gid == creds.fsgid() {
-            if (perm.may_read() && !mode.is_group_readable())
-                || (perm.may_write() && !mode.is_group_writable())
-                || (perm.may_exec() && !mode.is_group_executable())
-            {
-                return_errno_with_message!(Errno::EACCES, "group permission check failed");
-            }
-        } else if (perm.may_read() && !mode.is_other_readable())
-            || (perm.may_write() && !mode.is_other_writable())
-            || (perm.may_exec() && !mode.is_other_executable())
-        {
-            return_errno_with_message!(Errno::EACCES, "other permission check failed");
-        }
-
-        Ok(())
+    fn check_permission(&self, perm: Permission) -> Result<()> {
+        self.metadata().check_permission(perm)
     }
 }
 
@@ -595,6 +740,63 @@ impl dyn Inode {
         let mut reader = VmReader::from(buf).to_fallible();
         self.write_direct_at(offset, &mut reader)
     }
+
+    /// Copies at most `len` bytes from `src` at `src_offset` to this inode at `offset`.
+    ///
+    /// The file system of this inode is asked to accelerate the copy with
+    /// [`Inode::copy_range_from`] first. If it cannot, the data is copied from the page cache of
+    /// `src` to this inode, or via a bounce buffer if `src` has no page cache.
+    ///
+    /// The copy stops at the end of `src`. If an error occurs after some bytes are copied, the
+    /// number of the copied bytes is returned.
+    pub fn copy_from(
+        &self,
+        offset: usize,
+        src: &dyn Inode,
+        src_offset: usize,
+        len: usize,
+    ) -> Result<usize> {
+        match self.copy_range_from(offset, src, src_offset, len) {
+            Err(err) if err.error() == Errno::EOPNOTSUPP => (),
+            res => return res,
+        }
+
+        let src_size = src.size();
+        if src_offset >= src_size {
+            return Ok(0);
+        }
+        let len = len.min(src_size - src_offset);
+
+        let mut copied = 0;
+        let mut buffer = None;
+        while copied < len {
+            let src_pos = src_offset + copied;
+            let chunk_len = (PAGE_SIZE - src_pos % PAGE_SIZE).min(len - copied);
+
+            let copy_res = if let Some(page_cache) = src.page_cache() {
+                page_cache
+                    .commit_on(src_pos / PAGE_SIZE, CommitFlags::empty())
+                    .and_then(|frame| {
+                        let mut reader = frame.reader();
+                        reader.skip(src_pos % PAGE_SIZE).limit(chunk_len);
+                        self.write_at(offset + copied, &mut reader.to_fallible())
+                    })
+            } else {
+                let buffer = buffer.get_or_insert_with(|| vec![0u8; PAGE_SIZE]);
+                src.read_bytes_at(src_pos, &mut buffer[..chunk_len])
+                    .and_then(|read_len| self.write_bytes_at(offset + copied, &buffer[..read_len]))
+            };
+
+            match copy_res {
+                Ok(0) => break,
+                Ok(copy_len) => copied += copy_len,
+                Err(_) if copied > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(copied)
+    }
 }
 
 pub struct InodeWriter<'a> {
@@ -2,6 +2,11 @@
 
 use crate::prelude::*;
 
+/// An `ioctl` command.
+///
+/// Most commands are encoded with the `_IOC` macro in Linux, which packs the direction and the
+/// size of the argument into the command. Such information can be decoded with [`Self::dir`]
+/// and [`Self::size`]. The legacy commands (e.g., most TTY commands) carry no such information.
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 pub enum IoctlCmd {
@@ -12,23 +17,37 @@ pub enum IoctlCmd {
     TCSETSW = 0x5403,
     /// Drain the output buffer, and discard pending input, and set attributes
     TCSETSF = 0x5404,
+    /// Send a break, or wait until the output buffer is drained if the argument is nonzero
+    TCSBRK = 0x5409,
+    /// Discard the input buffer, the output buffer, or both
+    TCFLSH = 0x540b,
     /// Make the given terminal the controlling terminal of the calling process.
     TIOCSCTTY = 0x540e,
     /// Get the process group ID of the foreground process group on this terminal
     TIOCGPGRP = 0x540f,
     /// Set the foreground process group ID of this terminal.
     TIOCSPGRP = 0x5410,
+    /// Get the number of bytes in the output buffer.
+    TIOCOUTQ = 0x5411,
     /// Get the number of bytes in the input buffer.
     FIONREAD = 0x541B,
     /// Set window size
     TIOCGWINSZ = 0x5413,
     TIOCSWINSZ = 0x5414,
+    /// Enable or disable the packet mode of a pseudo terminal master
+    TIOCPKT = 0x5420,
     /// Enable or disable non-blocking I/O mode.
     FIONBIO = 0x5421,
     /// the calling process gives up this controlling terminal
     TIOCNOTTY = 0x5422,
     /// Return the session ID of FD
     TIOCGSID = 0x5429,
+    /// The `struct termios2` versions of `TCGETS`, `TCSETS`, `TCSETSW`, and `TCSETSF`, which
+    /// support arbitrary baud rates
+    TCGETS2 = 0x802c542a,
+    TCSETS2 = 0x402c542b,
+    TCSETSW2 = 0x402c542c,
+    TCSETSF2 = 0x402c542d,
     /// Clear the close on exec flag on a file descriptor
     FIONCLEX = 0x5450,
     /// Set the close on exec flag on a file descriptor
@@ -39,8 +58,309 @@ pub enum IoctlCmd {
     TIOCGPTN = 0x80045430,
     /// Lock/unlock Pty
     TIOCSPTLCK = 0x40045431,
+    /// Get the packet mode of a pseudo terminal master
+    TIOCGPKT = 0x80045438,
+    /// Get whether the Pty is locked
+    TIOCGPTLCK = 0x80045439,
     /// Safely open the slave
     TIOCGPTPEER = 0x40045441,
     /// Get tdx report using TDCALL
     TDXGETREPORT = 0xc4405401,
+    /// Set the number of retries of an I2C adapter
+    I2C_RETRIES = 0x0701,
+    /// Set the timeout of an I2C adapter
+    I2C_TIMEOUT = 0x0702,
+    /// Set the address of the I2C device to access
+    I2C_SLAVE = 0x0703,
+    /// Enable or disable 10-bit I2C addresses
+    I2C_TENBIT = 0x0704,
+    /// Get the functionality of an I2C adapter
+    I2C_FUNCS = 0x0705,
+    /// Set the address of the I2C device to access even if it is used by a driver
+    I2C_SLAVE_FORCE = 0x0706,
+    /// Perform combined I2C transfers
+    I2C_RDWR = 0x0707,
+    /// Enable or disable SMBus packet error checking
+    I2C_PEC = 0x0708,
+    /// Perform an SMBus transfer
+    I2C_SMBUS = 0x0720,
+    /// Get the information of a GPIO chip
+    GPIO_GET_CHIPINFO_IOCTL = 0x8044b401,
+    /// Get the information of a GPIO line
+    GPIO_V2_GET_LINEINFO_IOCTL = 0xc100b405,
+    /// Request GPIO lines
+    GPIO_V2_GET_LINE_IOCTL = 0xc250b407,
+    /// Change the configuration of the requested GPIO lines
+    GPIO_V2_LINE_SET_CONFIG_IOCTL = 0xc110b40d,
+    /// Get the values of the requested GPIO lines
+    GPIO_V2_LINE_GET_VALUES_IOCTL = 0xc010b40e,
+    /// Set the values of the requested GPIO lines
+    GPIO_V2_LINE_SET_VALUES_IOCTL = 0xc010b40f,
+    /// Get the mode of an SPI device
+    SPI_IOC_RD_MODE = 0x80016b01,
+    /// Set the mode of an SPI device
+    SPI_IOC_WR_MODE = 0x40016b01,
+    /// Get whether an SPI device transfers the least significant bit first
+    SPI_IOC_RD_LSB_FIRST = 0x80016b02,
+    /// Set whether an SPI device transfers the least significant bit first
+    SPI_IOC_WR_LSB_FIRST = 0x40016b02,
+    /// Get the number of bits per word of an SPI device
+    SPI_IOC_RD_BITS_PER_WORD = 0x80016b03,
+    /// Set the number of bits per word of an SPI device
+    SPI_IOC_WR_BITS_PER_WORD = 0x40016b03,
+    /// Get the maximum clock frequency of an SPI device
+    SPI_IOC_RD_MAX_SPEED_HZ = 0x80046b04,
+    /// Set the maximum clock frequency of an SPI device
+    SPI_IOC_WR_MAX_SPEED_HZ = 0x40046b04,
+    /// Get the 32-bit mode of an SPI device
+    SPI_IOC_RD_MODE32 = 0x80046b05,
+    /// Set the 32-bit mode of an SPI device
+    SPI_IOC_WR_MODE32 = 0x40046b05,
+    /// Perform an SPI message with N transfers, whose size is encoded in the command
+    SPI_IOC_MESSAGE_1 = 0x40206b00,
+    SPI_IOC_MESSAGE_2 = 0x40406b00,
+    SPI_IOC_MESSAGE_3 = 0x40606b00,
+    SPI_IOC_MESSAGE_4 = 0x40806b00,
+    SPI_IOC_MESSAGE_5 = 0x40a06b00,
+    SPI_IOC_MESSAGE_6 = 0x40c06b00,
+    SPI_IOC_MESSAGE_7 = 0x40e06b00,
+    SPI_IOC_MESSAGE_8 = 0x41006b00,
+    /// Get the capabilities of a PTP clock
+    PTP_CLOCK_GETCAPS = 0x80503d01,
+    /// Measure the offset between a PTP clock and the system clock
+    PTP_SYS_OFFSET = 0x43403d05,
+    /// Measure the offset between a PTP clock and the system clocks by cross timestamping
+    PTP_SYS_OFFSET_PRECISE = 0xc0403d08,
+    /// Measure the offset between a PTP clock and a system clock with the timestamps around
+    PTP_SYS_OFFSET_EXTENDED = 0xc4c03d09,
+    /// The second versions of the above PTP commands, which are handled in the same way
+    PTP_CLOCK_GETCAPS2 = 0x80503d0a,
+    PTP_SYS_OFFSET2 = 0x43403d0e,
+    PTP_SYS_OFFSET_PRECISE2 = 0xc0403d11,
+    PTP_SYS_OFFSET_EXTENDED2 = 0xc4c03d12,
+    /// Read the time of an RTC
+    RTC_RD_TIME = 0x80247009,
+    /// Set the time of an RTC
+    RTC_SET_TIME = 0x4024700a,
+    /// Get the information of a watchdog
+    WDIOC_GETSUPPORT = 0x80285700,
+    /// Get the status of a watchdog
+    WDIOC_GETSTATUS = 0x80045701,
+    /// Get the status of a watchdog at boot
+    WDIOC_GETBOOTSTATUS = 0x80045702,
+    /// Enable or disable a watchdog
+    WDIOC_SETOPTIONS = 0x80045704,
+    /// Ping a watchdog
+    WDIOC_KEEPALIVE = 0x80045705,
+    /// Set the timeout of a watchdog
+    WDIOC_SETTIMEOUT = 0xc0045706,
+    /// Get the timeout of a watchdog
+    WDIOC_GETTIMEOUT = 0x80045707,
+    /// Get the time left before a watchdog resets the system
+    WDIOC_GETTIMELEFT = 0x8004570a,
+    /// Get the flags of an inode
+    FS_IOC_GETFLAGS = 0x80086601,
+    /// Set the flags of an inode
+    FS_IOC_SETFLAGS = 0x40086602,
+    /// The 32-bit versions of `FS_IOC_GETFLAGS` and `FS_IOC_SETFLAGS`, which are handled in the
+    /// same way
+    FS_IOC32_GETFLAGS = 0x80046601,
+    FS_IOC32_SETFLAGS = 0x40046602,
+    /// Bind a loop device to a file
+    LOOP_SET_FD = 0x4c00,
+    /// Unbind a loop device from its file
+    LOOP_CLR_FD = 0x4c01,
+    /// Set the status of a loop device
+    LOOP_SET_STATUS64 = 0x4c04,
+    /// Get the status of a loop device
+    LOOP_GET_STATUS64 = 0x4c05,
+    /// Update the capacity of a loop device after the size of its file changes
+    LOOP_SET_CAPACITY = 0x4c07,
+    /// Enable or disable the direct I/O on the file of a loop device
+    LOOP_SET_DIRECT_IO = 0x4c08,
+    /// Bind a loop device to a file and set its status at once
+    LOOP_CONFIGURE = 0x4c0a,
+    /// Add a loop device
+    LOOP_CTL_ADD = 0x4c80,
+    /// Remove a loop device
+    LOOP_CTL_REMOVE = 0x4c81,
+    /// Get a free loop device, which is added if there is none
+    LOOP_CTL_GET_FREE = 0x4c82,
+    /// Re-read the partition table of a block device
+    BLKRRPART = 0x125f,
+    /// Get the size of a block device in sectors
+    BLKGETSIZE = 0x1260,
+    /// Get the logical block size of a block device
+    BLKSSZGET = 0x1268,
+    /// Get the size of a block device in bytes
+    BLKGETSIZE64 = 0x80081272,
+    /// Discard a range of bytes of a block device
+    BLKDISCARD = 0x1277,
+    /// Zero out a range of bytes of a block device
+    BLKZEROOUT = 0x127f,
+    /// Get the version of the device mapper interface
+    DM_VERSION = 0xc138fd00,
+    /// Remove all the mapped devices that are not open
+    DM_REMOVE_ALL = 0xc138fd01,
+    /// Create a mapped device
+    DM_DEV_CREATE = 0xc138fd03,
+    /// Remove a mapped device
+    DM_DEV_REMOVE = 0xc138fd04,
+    /// Suspend or resume a mapped device
+    DM_DEV_SUSPEND = 0xc138fd06,
+    /// Get the status of a mapped device
+    DM_DEV_STATUS = 0xc138fd07,
+    /// Load a table into the inactive slot of a mapped device
+    DM_TABLE_LOAD = 0xc138fd09,
+    /// Clear the inactive table of a mapped device
+    DM_TABLE_CLEAR = 0xc138fd0a,
+    /// Get the targets in the table of a mapped device
+    DM_TABLE_STATUS = 0xc138fd0c,
+    /// Get the version of the evdev interface
+    EVIOCGVERSION = 0x80044501,
+    /// Get the ID of an input device
+    EVIOCGID = 0x80084502,
+    /// Get the delay and the period of the key repeat
+    EVIOCGREP = 0x80084503,
+    /// Set the delay and the period of the key repeat
+    EVIOCSREP = 0x40084503,
+    /// Grab or release an input device
+    EVIOCGRAB = 0x40044590,
+    /// Set the clock of the event timestamps
+    EVIOCSCLOCKID = 0x400445a0,
+    // The following evdev commands encode the buffer sizes in the commands. Only the sizes that
+    // libevdev uses are supported, and the handlers get the sizes with `IoctlCmd::size`.
+    /// Get the name of an input device
+    EVIOCGNAME = 0x81004506,
+    /// Get the physical location of an input device
+    EVIOCGPHYS = 0x81004507,
+    /// Get the unique identifier of an input device
+    EVIOCGUNIQ = 0x81004508,
+    /// Get the properties of an input device
+    EVIOCGPROP = 0x80084509,
+    /// Get the state of the keys
+    EVIOCGKEY = 0x80604518,
+    /// Get the state of the LEDs
+    EVIOCGLED = 0x80084519,
+    /// Get the state of the switches
+    EVIOCGSW = 0x8008451b,
+    /// Get the supported event types
+    EVIOCGBIT_EV = 0x80084520,
+    /// Get the supported key codes
+    EVIOCGBIT_KEY = 0x80604521,
+    /// Get the supported relative axes
+    EVIOCGBIT_REL = 0x80084522,
+    /// Get the supported absolute axes
+    EVIOCGBIT_ABS = 0x80084523,
+    /// Get the supported miscellaneous events
+    EVIOCGBIT_MSC = 0x80084524,
+    /// Get the supported switches
+    EVIOCGBIT_SW = 0x80084525,
+    /// Get the supported LEDs
+    EVIOCGBIT_LED = 0x80084531,
+    /// Get the supported sounds
+    EVIOCGBIT_SND = 0x80084532,
+    /// Get the supported force feedback effects
+    EVIOCGBIT_FF = 0x80104535,
+    /// Get the version of the PCM interface
+    SNDRV_PCM_IOCTL_PVERSION = 0x80044100,
+    /// Get the information of a PCM device
+    SNDRV_PCM_IOCTL_INFO = 0x81204101,
+    /// Enable or disable the timestamps of a PCM stream
+    SNDRV_PCM_IOCTL_TSTAMP = 0x40044102,
+    /// Set the type of the timestamps of a PCM stream
+    SNDRV_PCM_IOCTL_TTSTAMP = 0x40044103,
+    /// Tell the version of the PCM interface that the user space uses
+    SNDRV_PCM_IOCTL_USER_PVERSION = 0x40044104,
+    /// Narrow the configuration space of the hardware parameters
+    SNDRV_PCM_IOCTL_HW_REFINE = 0xc2604110,
+    /// Choose and set the hardware parameters
+    SNDRV_PCM_IOCTL_HW_PARAMS = 0xc2604111,
+    /// Free the hardware parameters and the buffer
+    SNDRV_PCM_IOCTL_HW_FREE = 0x4112,
+    /// Set the software parameters
+    SNDRV_PCM_IOCTL_SW_PARAMS = 0xc0884113,
+    /// Get the number of the frames that are written but not played
+    SNDRV_PCM_IOCTL_DELAY = 0x80084121,
+    /// Synchronize the hardware pointer
+    SNDRV_PCM_IOCTL_HWSYNC = 0x4122,
+    /// Exchange the hardware pointer and the application pointer
+    SNDRV_PCM_IOCTL_SYNC_PTR = 0xc0884123,
+    /// Prepare a PCM stream to start
+    SNDRV_PCM_IOCTL_PREPARE = 0x4140,
+    /// Discard the data that is written but not played
+    SNDRV_PCM_IOCTL_RESET = 0x4141,
+    /// Start a PCM stream
+    SNDRV_PCM_IOCTL_START = 0x4142,
+    /// Stop a PCM stream immediately
+    SNDRV_PCM_IOCTL_DROP = 0x4143,
+    /// Stop a PCM stream after the written data is played
+    SNDRV_PCM_IOCTL_DRAIN = 0x4144,
+    /// Write the interleaved frames
+    SNDRV_PCM_IOCTL_WRITEI_FRAMES = 0x40184150,
+    /// Create a TUN/TAP device or attach to an existing one
+    TUNSETIFF = 0x400454ca,
+    /// Make a TUN/TAP device persistent or not
+    TUNSETPERSIST = 0x400454cb,
+    /// Get the features supported by TUN/TAP devices
+    TUNGETFEATURES = 0x800454cf,
+    /// Set the offloads that the user space can handle
+    TUNSETOFFLOAD = 0x400454d0,
+    /// Get the name and the flags of a TUN/TAP device
+    TUNGETIFF = 0x800454d2,
+    /// Get the length of the virtio network header
+    TUNGETVNETHDRSZ = 0x800454d7,
+    /// Set the length of the virtio network header
+    TUNSETVNETHDRSZ = 0x400454d8,
+    /// Attach or detach a queue of a multi-queue TUN/TAP device
+    TUNSETQUEUE = 0x400454d9,
+}
+
+/// The direction of the argument transfer of an `ioctl` command.
+///
+/// The direction is from the perspective of userspace, i.e., [`IoctlDir::Write`] means that
+/// userspace writes the argument to the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoctlDir {
+    None,
+    Write,
+    Read,
+    ReadWrite,
+}
+
+const IOC_NRBITS: u32 = 8;
+const IOC_TYPEBITS: u32 = 8;
+const IOC_SIZEBITS: u32 = 14;
+const IOC_SIZESHIFT: u32 = IOC_NRBITS + IOC_TYPEBITS;
+const IOC_DIRSHIFT: u32 = IOC_SIZESHIFT + IOC_SIZEBITS;
+
+impl IoctlCmd {
+    /// Decodes a raw `ioctl` command.
+    ///
+    /// Unknown commands are reported as [`Errno::ENOTTY`], which is what Linux returns for
+    /// commands that the file does not support.
+    pub fn decode(raw_cmd: u32) -> Result<Self> {
+        Self::try_from(raw_cmd)
+            .map_err(|_| Error::with_message(Errno::ENOTTY, "the ioctl command is unknown"))
+    }
+
+    /// Returns the direction of the argument transfer encoded in the command.
+    pub fn dir(self) -> IoctlDir {
+        match (self as u32) >> IOC_DIRSHIFT {
+            0 => IoctlDir::None,
+            1 => IoctlDir::Write,
+            2 => IoctlDir::Read,
+            _ => IoctlDir::ReadWrite,
+        }
+    }
+
+    /// Returns the size of the argument encoded in the command.
+    ///
+    /// The size is zero if the direction is [`IoctlDir::None`].
+    pub fn size(self) -> usize {
+        if self.dir() == IoctlDir::None {
+            return 0;
+        }
+        (((self as u32) >> IOC_SIZESHIFT) & ((1 << IOC_SIZEBITS) - 1)) as usize
+    }
 }
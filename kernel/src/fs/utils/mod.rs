@@ -7,16 +7,24 @@ pub use channel::{Channel, Consumer, Producer};
 pub use creation_flags::CreationFlags;
 pub use dirent_visitor::DirentVisitor;
 pub use direntry_vec::DirEntryVecExt;
+#[cfg(ktest)]
+pub use exerciser::{
+    check_crash_consistency, run_benchmarks, FsBenchResult, FsExerciser, FsModel, MemoryDisk,
+};
 pub use falloc_mode::FallocMode;
 pub use file_creation_mask::FileCreationMask;
 pub use flock::{FlockItem, FlockList, FlockType};
 pub use fs::{FileSystem, FsFlags, SuperBlock};
-pub use inode::{Extension, Inode, InodeMode, InodeType, Metadata, MknodType, Permission};
-pub use ioctl::IoctlCmd;
-pub use page_cache::{CachePage, PageCache, PageCacheBackend};
+pub use inode::{
+    ExtendedMetadata, Extension, FileAttributes, Inode, InodeFlags, InodeMode, InodeType, Metadata,
+    MknodType, Permission,
+};
+pub use ioctl::{IoctlCmd, IoctlDir};
+pub use page_cache::{nr_cache_pages, CachePage, PageCache, PageCacheBackend};
 pub use random_test::{generate_random_operation, new_fs_in_memory};
 pub use range_lock::{
-    FileRange, RangeLockItem, RangeLockItemBuilder, RangeLockList, RangeLockType, OFFSET_MAX,
+    FileRange, RangeLockItem, RangeLockItemBuilder, RangeLockList, RangeLockOwner, RangeLockType,
+    OFFSET_MAX,
 };
 pub use status_flags::StatusFlags;
 pub use xattr::{
@@ -29,6 +37,8 @@ mod channel;
 mod creation_flags;
 mod dirent_visitor;
 mod direntry_vec;
+#[cfg(ktest)]
+mod exerciser;
 mod falloc_mode;
 mod file_creation_mask;
 mod flock;
@@ -53,6 +63,10 @@ pub enum SeekFrom {
     Start(usize),
     End(isize),
     Current(isize),
+    /// The start of the next data region at or after the offset.
+    Data(usize),
+    /// The start of the next hole at or after the offset.
+    Hole(usize),
 }
 
 /// Maximum bytes in a path
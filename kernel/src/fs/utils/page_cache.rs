@@ -5,7 +5,7 @@
 use core::{
     iter,
     ops::Range,
-    sync::atomic::{AtomicU8, Ordering},
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
 };
 
 use align_ext::AlignExt;
@@ -498,15 +498,36 @@ pub struct CachePageMeta {
 
 impl_untyped_frame_meta_for!(CachePageMeta);
 
+/// The number of pages allocated for the page caches.
+static NR_CACHE_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of pages allocated for the page caches in the system.
+pub fn nr_cache_pages() -> usize {
+    NR_CACHE_PAGES.load(Ordering::Relaxed)
+}
+
+impl CachePageMeta {
+    fn new(state: PageState) -> Self {
+        NR_CACHE_PAGES.fetch_add(1, Ordering::Relaxed);
+        Self {
+            state: AtomicPageState::new(state),
+        }
+    }
+}
+
+impl Drop for CachePageMeta {
+    fn drop(&mut self) {
+        NR_CACHE_PAGES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 pub trait CachePageExt {
     /// Gets the metadata associated with the cache page.
     fn metadata(&self) -> &CachePageMeta;
 
     /// Allocates a new cache page which content and state are uninitialized.
     fn alloc_uninit() -> Result<CachePage> {
-        let meta = CachePageMeta {
-            state: AtomicPageState::new(PageState::Uninit),
-        };
+        let meta = CachePageMeta::new(PageState::Uninit);
         let page = FrameAllocOptions::new()
             .zeroed(false)
             .alloc_frame_with(meta)?;
@@ -515,9 +536,7 @@ pub trait CachePageExt {
 
     /// Allocates a new zeroed cache page with the wanted state.
     fn alloc_zero(state: PageState) -> Result<CachePage> {
-        let meta = CachePageMeta {
-            state: AtomicPageState::new(state),
-        };
+        let meta = CachePageMeta::new(state);
         let page = FrameAllocOptions::new()
             .zeroed(true)
             .alloc_frame_with(meta)?;
@@ -9,16 +9,18 @@ use crate::process::Pid;
 ///
 /// ```no_run
 /// let mut lock = RangeLockItemBuilder::new()
+///     .owner(owner)
 ///     .type_(lock_type)
 ///     .range(from_c_flock_and_file(&lock_mut_c, file.clone())?)
 ///     .build()?;
 /// ```
 pub struct RangeLockItemBuilder {
     // Mandatory field
+    owner: Option<RangeLockOwner>,
     type_: Option<RangeLockType>,
     range: Option<FileRange>,
     // Optional fields
-    owner: Option<Pid>,
+    pid: Option<Pid>,
     waitqueue: Option<WaitQueue>,
 }
 
@@ -32,17 +34,23 @@ impl RangeLockItemBuilder {
     pub fn new() -> Self {
         Self {
             owner: None,
+            pid: None,
             type_: None,
             range: None,
             waitqueue: None,
         }
     }
 
-    pub fn owner(mut self, owner: Pid) -> Self {
+    pub fn owner(mut self, owner: RangeLockOwner) -> Self {
         self.owner = Some(owner);
         self
     }
 
+    pub fn pid(mut self, pid: Pid) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
     pub fn type_(mut self, type_: RangeLockType) -> Self {
         self.type_ = Some(type_);
         self
@@ -59,7 +67,12 @@ impl RangeLockItemBuilder {
     }
 
     pub fn build(self) -> Result<RangeLockItem> {
-        let owner = self.owner.unwrap_or_else(|| current!().pid());
+        let owner = if let Some(owner) = self.owner {
+            owner
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "owner is mandatory");
+        };
+        let pid = self.pid.unwrap_or_else(|| current!().pid());
         let type_ = if let Some(type_) = self.type_ {
             type_
         } else {
@@ -77,6 +90,7 @@ impl RangeLockItemBuilder {
         Ok(RangeLockItem {
             lock: RangeLock {
                 owner,
+                pid,
                 type_,
                 range,
             },
@@ -14,11 +14,30 @@ use crate::{prelude::*, process::Pid};
 mod builder;
 mod range;
 
+/// The owner of a POSIX advisory file range lock.
+///
+/// Locks with different owners conflict with each other, even if they are held by the same
+/// process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeLockOwner {
+    /// A traditional POSIX lock, which is owned by a file table.
+    ///
+    /// Since a file table is private to a process unless `CLONE_FILES` is used, the lock is
+    /// effectively owned by the process. The value is the address of the file table.
+    FileTable(usize),
+    /// An open file description (OFD) lock, which is owned by an opened file.
+    ///
+    /// The value is the address of the opened file.
+    OpenFile(usize),
+}
+
 /// The metadata of a POSIX advisory file range lock.
 #[derive(Debug, Clone)]
 struct RangeLock {
-    /// Owner of the lock, representing the process holding the lock
-    owner: Pid,
+    /// Owner of the lock
+    owner: RangeLockOwner,
+    /// Process that sets the lock, which is reported to the user
+    pid: Pid,
     /// Type of lock: can be F_RDLCK (read lock), F_WRLCK (write lock), or F_UNLCK (unlock)
     type_: RangeLockType,
     /// Range of the lock which specifies the portion of the file being locked
@@ -46,16 +65,31 @@ impl RangeLockItem {
         self.lock.type_ = type_;
     }
 
-    /// Returns the owner (process ID) of the lock
-    pub fn owner(&self) -> Pid {
+    /// Returns the owner of the lock
+    pub fn owner(&self) -> RangeLockOwner {
         self.lock.owner
     }
 
-    /// Sets the owner of the lock to the specified process ID
-    pub fn set_owner(&mut self, owner: Pid) {
+    /// Sets the owner of the lock to the specified owner
+    pub fn set_owner(&mut self, owner: RangeLockOwner) {
         self.lock.owner = owner;
     }
 
+    /// Returns the ID of the process that sets the lock
+    pub fn pid(&self) -> Pid {
+        self.lock.pid
+    }
+
+    /// Sets the ID of the process that sets the lock
+    pub fn set_pid(&mut self, pid: Pid) {
+        self.lock.pid = pid;
+    }
+
+    /// Returns whether the lock is an open file description lock
+    pub fn is_ofd(&self) -> bool {
+        matches!(self.owner(), RangeLockOwner::OpenFile(_))
+    }
+
     /// Returns the range of the lock
     pub fn range(&self) -> FileRange {
         self.lock.range
@@ -69,7 +103,7 @@ impl RangeLockItem {
     /// Checks if this lock conflicts with another lock
     /// Returns true if there is a conflict, otherwise false
     pub fn conflict_with(&self, other: &Self) -> bool {
-        // If locks are owned by the same process, they do not conflict
+        // If locks have the same owner, they do not conflict
         if self.owner() == other.owner() {
             return false;
         }
@@ -160,6 +194,7 @@ impl Debug for RangeLockItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RangeLock")
             .field("owner", &self.owner())
+            .field("pid", &self.pid())
             .field("type_", &self.type_())
             .field("range", &self.range())
             .finish()
@@ -180,7 +215,7 @@ impl Clone for RangeLockItem {
 /// List of File POSIX advisory range locks.
 ///
 /// Rule of ordering:
-/// Locks are sorted by owner process, then by the starting offset.
+/// Locks are sorted by owner, then by the starting offset.
 ///
 /// Rule of merging:
 /// Adjacent and overlapping locks with same owner and type will be merged.
@@ -210,6 +245,7 @@ impl RangeLockList {
         for existing_lock in list.iter() {
             if lock.conflict_with(existing_lock) {
                 req_lock.set_owner(existing_lock.owner());
+                req_lock.set_pid(existing_lock.pid());
                 req_lock.set_type(existing_lock.type_());
                 req_lock.set_range(existing_lock.range());
                 return req_lock;
@@ -223,13 +259,19 @@ impl RangeLockList {
     ///
     /// If no conflicting locks exist, the lock is set and the function returns `Ok(())`.
     /// If a conflicting lock exists:
-    /// - If waker is not `None`, it is added to the conflicting lock's waitqueue, and the function returns `EAGAIN`.
-    /// - If waker is `None`, the function returns `EAGAIN`.
-    fn try_set_lock(&self, req_lock: &RangeLockItem, waker: Option<&Arc<Waker>>) -> Result<()> {
+    /// - If `blocked` is not `None`, the request is blocked on the conflicting lock, and the
+    ///   function returns `EAGAIN`. If blocking would cause a deadlock, the function returns
+    ///   `EDEADLK` instead.
+    /// - If `blocked` is `None`, the function returns `EAGAIN`.
+    fn try_set_lock(
+        &self,
+        req_lock: &RangeLockItem,
+        blocked: Option<&mut BlockedRequest>,
+    ) -> Result<()> {
         let mut list = self.inner.write();
         if let Some(conflict_lock) = list.iter().find(|l| req_lock.conflict_with(l)) {
-            if let Some(waker) = waker {
-                conflict_lock.waitqueue.enqueue(waker.clone());
+            if let Some(blocked) = blocked {
+                blocked.block_on(conflict_lock)?;
             }
             return_errno_with_message!(Errno::EAGAIN, "the file is locked");
         } else {
@@ -242,6 +284,7 @@ impl RangeLockList {
     ///
     /// If the lock is non-blocking and there is a conflict, return `Err(Errno::EAGAIN)`.
     /// Otherwise, block the current process until the lock can be set or it is interrupted by a signal.
+    /// If the blocking would cause a deadlock, return `Err(Errno::EDEADLK)`.
     pub fn set_lock(&self, req_lock: &RangeLockItem, is_nonblocking: bool) -> Result<()> {
         debug!(
             "set_lock with RangeLock: {:?}, is_nonblocking: {}",
@@ -251,8 +294,9 @@ impl RangeLockList {
             self.try_set_lock(req_lock, None)
         } else {
             let (waiter, waker) = Waiter::new_pair();
+            let mut blocked = BlockedRequest::new(req_lock.owner(), waker);
             waiter.pause_until(|| {
-                let result = self.try_set_lock(req_lock, Some(&waker));
+                let result = self.try_set_lock(req_lock, Some(&mut blocked));
                 if result.is_err_and(|err| err.error() == Errno::EAGAIN) {
                     None
                 } else {
@@ -406,12 +450,120 @@ impl RangeLockList {
     }
 }
 
+impl RangeLockList {
+    /// Releases all the locks of the owner.
+    ///
+    /// The processes waiting for the released locks are woken.
+    pub fn release(&self, owner: RangeLockOwner) {
+        debug!("release locks of owner: {:?}", owner);
+        self.inner.write().retain(|lk| lk.owner() != owner);
+    }
+}
+
 impl Default for RangeLockList {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// The maximum number of steps to walk the waits-for graph when detecting deadlocks.
+///
+/// Like Linux, we give up the detection for long chains, which are unlikely to be deadlocks.
+const MAX_DEADLOCK_STEPS: usize = 10;
+
+/// The edges of the waits-for graph.
+///
+/// Each edge is a blocked request of a traditional POSIX lock, which consists of the owner of
+/// the request and the owner of the lock that blocks the request. The graph is global because a
+/// deadlock may involve locks on different files.
+static BLOCKED_REQUESTS: Mutex<Vec<(RangeLockOwner, RangeLockOwner)>> = Mutex::new(Vec::new());
+
+/// A request that is blocked by a conflicting lock.
+///
+/// The request is recorded in the waits-for graph until it is dropped.
+struct BlockedRequest {
+    owner: RangeLockOwner,
+    waker: Arc<Waker>,
+    blocker: Option<RangeLockOwner>,
+}
+
+impl BlockedRequest {
+    fn new(owner: RangeLockOwner, waker: Arc<Waker>) -> Self {
+        Self {
+            owner,
+            waker,
+            blocker: None,
+        }
+    }
+
+    /// Blocks the request on the conflicting lock.
+    ///
+    /// Only requests of traditional POSIX locks are checked for deadlocks, following Linux.
+    /// This is because an OFD lock is not associated with a single thread of execution, so a
+    /// blocked OFD request does not prevent its owner from releasing other locks.
+    fn block_on(&mut self, conflict_lock: &RangeLockItem) -> Result<()> {
+        let mut blocked_requests = BLOCKED_REQUESTS.lock();
+
+        if let Some(blocker) = self.blocker.take() {
+            Self::remove_edge(&mut blocked_requests, self.owner, blocker);
+        }
+
+        if let RangeLockOwner::FileTable(_) = self.owner {
+            if Self::is_deadlock(&blocked_requests, self.owner, conflict_lock.owner()) {
+                return_errno_with_message!(Errno::EDEADLK, "a deadlock would occur");
+            }
+            blocked_requests.push((self.owner, conflict_lock.owner()));
+            self.blocker = Some(conflict_lock.owner());
+        }
+        drop(blocked_requests);
+
+        conflict_lock.waitqueue.enqueue(self.waker.clone());
+        Ok(())
+    }
+
+    /// Returns whether blocking `owner` on `blocker` would close a cycle in the waits-for graph.
+    fn is_deadlock(
+        blocked_requests: &[(RangeLockOwner, RangeLockOwner)],
+        owner: RangeLockOwner,
+        blocker: RangeLockOwner,
+    ) -> bool {
+        let mut current = blocker;
+        for _ in 0..MAX_DEADLOCK_STEPS {
+            if current == owner {
+                return true;
+            }
+            match blocked_requests
+                .iter()
+                .find(|(waiting, _)| *waiting == current)
+            {
+                Some((_, next)) => current = *next,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    fn remove_edge(
+        blocked_requests: &mut Vec<(RangeLockOwner, RangeLockOwner)>,
+        owner: RangeLockOwner,
+        blocker: RangeLockOwner,
+    ) {
+        let idx = blocked_requests
+            .iter()
+            .position(|edge| *edge == (owner, blocker))
+            .unwrap();
+        blocked_requests.swap_remove(idx);
+    }
+}
+
+impl Drop for BlockedRequest {
+    fn drop(&mut self) {
+        if let Some(blocker) = self.blocker.take() {
+            Self::remove_edge(&mut BLOCKED_REQUESTS.lock(), self.owner, blocker);
+        }
+    }
+}
+
 /// Type of file range lock, aligned with Linux kernel.
 /// F_RDLCK = 0, F_WRLCK = 1, F_UNLCK = 2,
 #[derive(Debug, Copy, Clone, PartialEq, TryFromInt)]
@@ -31,21 +31,23 @@ pub struct XattrName<'a> {
 
 impl XattrNamespace {
     pub fn try_from_full_name(full_name: &str) -> Option<XattrNamespace> {
-        const USER_PREFIX: &str = "user.";
-        const TRUSTED_PREFIX: &str = "trusted.";
-        const SYSTEM_PREFIX: &str = "system.";
-        const SECURITY_PREFIX: &str = "security.";
+        [
+            XattrNamespace::User,
+            XattrNamespace::Trusted,
+            XattrNamespace::System,
+            XattrNamespace::Security,
+        ]
+        .into_iter()
+        .find(|namespace| full_name.starts_with(namespace.prefix()))
+    }
 
-        if full_name.starts_with(USER_PREFIX) {
-            Some(XattrNamespace::User)
-        } else if full_name.starts_with(TRUSTED_PREFIX) {
-            Some(XattrNamespace::Trusted)
-        } else if full_name.starts_with(SYSTEM_PREFIX) {
-            Some(XattrNamespace::System)
-        } else if full_name.starts_with(SECURITY_PREFIX) {
-            Some(XattrNamespace::Security)
-        } else {
-            None
+    /// Returns the prefix of the xattr names in the namespace.
+    pub const fn prefix(&self) -> &'static str {
+        match self {
+            XattrNamespace::User => "user.",
+            XattrNamespace::Trusted => "trusted.",
+            XattrNamespace::System => "system.",
+            XattrNamespace::Security => "security.",
         }
     }
 
@@ -56,6 +58,16 @@ impl XattrNamespace {
     pub fn is_admin(&self) -> bool {
         matches!(self, XattrNamespace::Trusted)
     }
+
+    /// Returns whether the xattrs in the namespace are listed when
+    /// listing xattrs in `list_namespace`.
+    ///
+    /// Listing in the trusted namespace shows all the xattrs, while
+    /// listing in other namespaces hides the trusted xattrs, which are
+    /// only visible to privileged users.
+    pub fn is_listed_in(&self, list_namespace: XattrNamespace) -> bool {
+        list_namespace.is_admin() || !self.is_admin()
+    }
 }
 
 impl<'a> XattrName<'a> {
@@ -0,0 +1,501 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_virtio::{
+    device::transport9p::{device::Transport9PDevice, get_device},
+    queue::QueueError,
+};
+use id_alloc::IdAlloc;
+use ostd::sync::WaitQueue;
+
+use super::protocol::{
+    parse_reply, reply_tag, Decoder, Dirent, Encoder, GetattrMask, MsgType, Qid, SetAttr, Stat,
+    StatFs, HEADER_SIZE, IOHDRSZ, NOFID, NOTAG, READDIRHDRSZ, VERSION_9P2000_L,
+};
+use crate::prelude::*;
+
+/// The maximum message size, which limits the contiguous buffers of the replies.
+pub(super) const MAX_MSIZE: u32 = 512 * 1024;
+
+/// The minimum message size, which can hold the replies of all the messages except for the data.
+pub(super) const MIN_MSIZE: u32 = 4096;
+
+/// The maximum number of the fids that are in use.
+const MAX_FIDS: usize = 1 << 16;
+
+/// The tags of the devices that are mounted.
+///
+/// Like Linux, a device can only be mounted once at a time.
+static MOUNTED_TAGS: SpinLock<BTreeSet<String>> = SpinLock::new(BTreeSet::new());
+
+/// A 9P client over a virtio-9p device.
+///
+/// The requests are sent concurrently and are distinguished by their tags. A waiting thread
+/// takes the replies from the device and wakes up the threads that the replies belong to.
+pub(super) struct Client {
+    device: Arc<Transport9PDevice>,
+    /// The maximum size of a message, which is negotiated with `Tversion`.
+    msize: u32,
+    /// The replies that have been taken from the device, indexed by the tags.
+    replies: SpinLock<BTreeMap<u16, Vec<u8>>>,
+    /// The tags of the requests that are being processed.
+    tags: SpinLock<TagAlloc>,
+    fids: SpinLock<IdAlloc>,
+    wait_queue: Arc<WaitQueue>,
+}
+
+#[derive(Default)]
+struct TagAlloc {
+    in_use: BTreeSet<u16>,
+    next: u16,
+}
+
+impl TagAlloc {
+    fn alloc(&mut self) -> Option<u16> {
+        // `NOTAG` is reserved for `Tversion`.
+        if self.in_use.len() >= NOTAG as usize {
+            return None;
+        }
+        loop {
+            let tag = self.next;
+            self.next = self.next.wrapping_add(1) % NOTAG;
+            if self.in_use.insert(tag) {
+                return Some(tag);
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Connects to the device with the tag and negotiates the message size.
+    pub(super) fn connect(tag: &str, msize: u32) -> Result<Arc<Self>> {
+        let device = get_device(tag).ok_or_else(|| {
+            Error::with_message(Errno::ENOENT, "the virtio-9p tag does not exist")
+        })?;
+        if !MOUNTED_TAGS.lock().insert(tag.to_string()) {
+            return_errno_with_message!(Errno::EBUSY, "the virtio-9p device is already mounted");
+        }
+
+        let wait_queue = Arc::new(WaitQueue::new());
+        {
+            let wait_queue = wait_queue.clone();
+            device.set_callback(Box::new(move || {
+                wait_queue.wake_all();
+            }));
+        }
+
+        let mut client = Self {
+            device,
+            msize: msize.clamp(MIN_MSIZE, MAX_MSIZE),
+            replies: SpinLock::new(BTreeMap::new()),
+            tags: SpinLock::new(TagAlloc::default()),
+            fids: SpinLock::new(IdAlloc::with_capacity(MAX_FIDS)),
+            wait_queue,
+        };
+        client.version()?;
+        Ok(Arc::new(client))
+    }
+
+    /// Returns the maximum size of the data in `Tread` or `Twrite`.
+    pub(super) fn max_io_size(&self) -> usize {
+        self.msize as usize - IOHDRSZ
+    }
+
+    /// Negotiates the version and the message size with `Tversion`.
+    fn version(&mut self) -> Result<()> {
+        let request = Encoder::new(MsgType::Tversion)
+            .u32(self.msize)
+            .str(VERSION_9P2000_L);
+        let reply = self.send(request, NOTAG, self.msize as usize)?;
+
+        let mut decoder = Decoder::new(&reply[HEADER_SIZE..]);
+        let msize = decoder.u32()?;
+        let version = decoder.str()?;
+        if version != VERSION_9P2000_L {
+            return_errno_with_message!(Errno::EINVAL, "the 9P server does not support 9P2000.L");
+        }
+        if msize < MIN_MSIZE {
+            return_errno_with_message!(Errno::EREMOTEIO, "the 9P message size is too small");
+        }
+        self.msize = self.msize.min(msize);
+        Ok(())
+    }
+
+    /// Sends a request and waits for the reply, whose body is returned.
+    fn request(&self, request: Encoder, reply_len: usize) -> Result<Vec<u8>> {
+        let tag = self.wait_queue.wait_until(|| self.tags.lock().alloc());
+        let result = self.send(request, tag, reply_len);
+        self.tags.lock().in_use.remove(&tag);
+        self.wait_queue.wake_all();
+
+        let mut reply = result?;
+        reply.drain(..HEADER_SIZE);
+        Ok(reply)
+    }
+
+    /// Sends a request with the tag and waits for the reply.
+    fn send(&self, request: Encoder, tag: u16, reply_len: usize) -> Result<Vec<u8>> {
+        let type_ = request.type_();
+        let bytes = request.finish(tag);
+        if bytes.len() > self.msize as usize {
+            return_errno_with_message!(Errno::EINVAL, "the 9P request is too large");
+        }
+
+        loop {
+            let num_submitted = self.device.num_submitted();
+            match self
+                .device
+                .submit(&bytes, reply_len.min(self.msize as usize))
+            {
+                Ok(()) => break,
+                // Wait until some requests are completed, which frees the descriptors.
+                Err(QueueError::BufferTooSmall) => self
+                    .wait_queue
+                    .wait_until(|| (self.device.num_submitted() < num_submitted).then_some(())),
+                Err(err) => {
+                    warn!("failed to submit the 9P request: {:?}", err);
+                    return_errno_with_message!(Errno::EIO, "the 9P request cannot be submitted");
+                }
+            }
+        }
+
+        // The requests are not flushed with `Tflush`, so the replies are waited for even if the
+        // thread is interrupted.
+        let mut reply = self.wait_queue.wait_until(|| {
+            self.collect_replies();
+            self.replies.lock().remove(&tag)
+        });
+
+        let (_, body) = parse_reply(&reply, type_)?;
+        let len = HEADER_SIZE + body.len();
+        reply.truncate(len);
+        Ok(reply)
+    }
+
+    /// Takes the replies from the device and wakes up the threads that wait for them.
+    fn collect_replies(&self) {
+        let mut has_replies = false;
+        while let Some(reply) = self.device.pop_reply() {
+            let Some(tag) = reply_tag(&reply) else {
+                warn!("the 9P reply is too short");
+                continue;
+            };
+            self.replies.lock().insert(tag, reply);
+            has_replies = true;
+        }
+
+        if has_replies {
+            self.wait_queue.wake_all();
+        }
+    }
+
+    /// Allocates a fid, which refers to no file until it is used by a request.
+    fn alloc_fid(self: &Arc<Self>) -> Result<Fid> {
+        let fid = self
+            .fids
+            .lock()
+            .alloc()
+            .ok_or_else(|| Error::with_message(Errno::ENFILE, "too many 9P fids"))?;
+        Ok(Fid {
+            fid: fid as u32,
+            client: self.clone(),
+            is_used: true,
+        })
+    }
+
+    /// Attaches to the root of the file tree, returning the fid of the root.
+    pub(super) fn attach(
+        self: &Arc<Self>,
+        uname: &str,
+        aname: &str,
+        n_uname: u32,
+    ) -> Result<(Fid, Qid)> {
+        let fid = self.alloc_fid()?;
+        let request = Encoder::new(MsgType::Tattach)
+            .u32(fid.fid)
+            .u32(NOFID)
+            .str(uname)
+            .str(aname)
+            .u32(n_uname);
+        let reply = self.request(request, PAGE_SIZE)?;
+        let qid = Decoder::new(&reply).qid()?;
+        Ok((fid, qid))
+    }
+
+    /// Walks from the fid to the file of the names, returning the fid of the file.
+    ///
+    /// If there are no names, the fid is cloned.
+    pub(super) fn walk(self: &Arc<Self>, fid: &Fid, names: &[&str]) -> Result<Fid> {
+        let new_fid = self.alloc_fid()?;
+        let mut request = Encoder::new(MsgType::Twalk)
+            .u32(fid.fid)
+            .u32(new_fid.fid)
+            .u16(names.len() as u16);
+        for name in names {
+            request = request.str(name);
+        }
+
+        let reply = match self.request(request, PAGE_SIZE) {
+            Ok(reply) => reply,
+            Err(err) => {
+                // The new fid is not used if the walk fails.
+                new_fid.forget();
+                return Err(err);
+            }
+        };
+        let nwqid = Decoder::new(&reply).u16()? as usize;
+        if nwqid < names.len() {
+            new_fid.forget();
+            return_errno_with_message!(Errno::ENOENT, "the 9P file does not exist");
+        }
+        Ok(new_fid)
+    }
+
+    pub(super) fn getattr(&self, fid: &Fid) -> Result<Stat> {
+        let request = Encoder::new(MsgType::Tgetattr)
+            .u32(fid.fid)
+            .u64(GetattrMask::BASIC.bits());
+        let reply = self.request(request, PAGE_SIZE)?;
+        Decoder::new(&reply).stat()
+    }
+
+    pub(super) fn setattr(&self, fid: &Fid, attr: &SetAttr) -> Result<()> {
+        let request = Encoder::new(MsgType::Tsetattr)
+            .u32(fid.fid)
+            .u32(attr.valid.bits())
+            .u32(attr.mode)
+            .u32(attr.uid)
+            .u32(attr.gid)
+            .u64(attr.size)
+            .time(attr.atime)
+            .time(attr.mtime);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn statfs(&self, fid: &Fid) -> Result<StatFs> {
+        let request = Encoder::new(MsgType::Tstatfs).u32(fid.fid);
+        let reply = self.request(request, PAGE_SIZE)?;
+        Decoder::new(&reply).statfs()
+    }
+
+    /// Opens the file of the fid with the Linux open flags, returning the I/O unit.
+    pub(super) fn lopen(&self, fid: &Fid, flags: u32) -> Result<u32> {
+        let request = Encoder::new(MsgType::Tlopen).u32(fid.fid).u32(flags);
+        let reply = self.request(request, PAGE_SIZE)?;
+        let mut decoder = Decoder::new(&reply);
+        let _qid = decoder.qid()?;
+        decoder.u32()
+    }
+
+    /// Creates and opens a regular file in the directory of the fid.
+    ///
+    /// The fid is changed to refer to the new file, which is opened with the flags.
+    pub(super) fn lcreate(
+        &self,
+        fid: &Fid,
+        name: &str,
+        flags: u32,
+        mode: u32,
+        gid: u32,
+    ) -> Result<u32> {
+        let request = Encoder::new(MsgType::Tlcreate)
+            .u32(fid.fid)
+            .str(name)
+            .u32(flags)
+            .u32(mode)
+            .u32(gid);
+        let reply = self.request(request, PAGE_SIZE)?;
+        let mut decoder = Decoder::new(&reply);
+        let _qid = decoder.qid()?;
+        decoder.u32()
+    }
+
+    pub(super) fn mkdir(&self, dir_fid: &Fid, name: &str, mode: u32, gid: u32) -> Result<()> {
+        let request = Encoder::new(MsgType::Tmkdir)
+            .u32(dir_fid.fid)
+            .str(name)
+            .u32(mode)
+            .u32(gid);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn mknod(
+        &self,
+        dir_fid: &Fid,
+        name: &str,
+        mode: u32,
+        (major, minor): (u32, u32),
+        gid: u32,
+    ) -> Result<()> {
+        let request = Encoder::new(MsgType::Tmknod)
+            .u32(dir_fid.fid)
+            .str(name)
+            .u32(mode)
+            .u32(major)
+            .u32(minor)
+            .u32(gid);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn symlink(&self, dir_fid: &Fid, name: &str, target: &str, gid: u32) -> Result<()> {
+        let request = Encoder::new(MsgType::Tsymlink)
+            .u32(dir_fid.fid)
+            .str(name)
+            .str(target)
+            .u32(gid);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn readlink(&self, fid: &Fid) -> Result<String> {
+        let request = Encoder::new(MsgType::Treadlink).u32(fid.fid);
+        let reply = self.request(request, self.msize as usize)?;
+        Decoder::new(&reply).str()
+    }
+
+    pub(super) fn link(&self, dir_fid: &Fid, fid: &Fid, name: &str) -> Result<()> {
+        let request = Encoder::new(MsgType::Tlink)
+            .u32(dir_fid.fid)
+            .u32(fid.fid)
+            .str(name);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn renameat(
+        &self,
+        old_dir_fid: &Fid,
+        old_name: &str,
+        new_dir_fid: &Fid,
+        new_name: &str,
+    ) -> Result<()> {
+        let request = Encoder::new(MsgType::Trenameat)
+            .u32(old_dir_fid.fid)
+            .str(old_name)
+            .u32(new_dir_fid.fid)
+            .str(new_name);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    pub(super) fn unlinkat(&self, dir_fid: &Fid, name: &str, flags: u32) -> Result<()> {
+        let request = Encoder::new(MsgType::Tunlinkat)
+            .u32(dir_fid.fid)
+            .str(name)
+            .u32(flags);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    /// Reads at most `count` bytes from the opened fid.
+    pub(super) fn read(&self, fid: &Fid, offset: u64, count: usize) -> Result<Vec<u8>> {
+        let count = count.min(self.max_io_size());
+        let request = Encoder::new(MsgType::Tread)
+            .u32(fid.fid)
+            .u64(offset)
+            .u32(count as u32);
+        let reply = self.request(request, IOHDRSZ + count)?;
+
+        let mut decoder = Decoder::new(&reply);
+        let len = decoder.u32()? as usize;
+        if len > count {
+            return_errno_with_message!(Errno::EIO, "the 9P server reads too much data");
+        }
+        Ok(decoder.bytes(len)?.to_vec())
+    }
+
+    /// Writes the data to the opened fid, returning the number of the written bytes.
+    pub(super) fn write(&self, fid: &Fid, offset: u64, data: &[u8]) -> Result<usize> {
+        let data = &data[..data.len().min(self.max_io_size())];
+        let request = Encoder::new(MsgType::Twrite)
+            .u32(fid.fid)
+            .u64(offset)
+            .u32(data.len() as u32)
+            .bytes(data);
+        let reply = self.request(request, PAGE_SIZE)?;
+        let written = Decoder::new(&reply).u32()? as usize;
+        Ok(written.min(data.len()))
+    }
+
+    /// Reads the directory entries from the opened fid, starting at the offset.
+    pub(super) fn readdir(&self, fid: &Fid, offset: u64) -> Result<Vec<Dirent>> {
+        let count = self.msize as usize - READDIRHDRSZ;
+        let request = Encoder::new(MsgType::Treaddir)
+            .u32(fid.fid)
+            .u64(offset)
+            .u32(count as u32);
+        let reply = self.request(request, self.msize as usize)?;
+
+        let mut decoder = Decoder::new(&reply);
+        let len = decoder.u32()? as usize;
+        let mut decoder = Decoder::new(decoder.bytes(len)?);
+        let mut entries = Vec::new();
+        while !decoder.is_empty() {
+            entries.push(decoder.dirent()?);
+        }
+        Ok(entries)
+    }
+
+    pub(super) fn fsync(&self, fid: &Fid, is_datasync: bool) -> Result<()> {
+        let request = Encoder::new(MsgType::Tfsync)
+            .u32(fid.fid)
+            .u32(is_datasync as u32);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+
+    fn clunk(&self, fid: u32) -> Result<()> {
+        let request = Encoder::new(MsgType::Tclunk).u32(fid);
+        self.request(request, PAGE_SIZE)?;
+        Ok(())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // The replies of the old session must not be mistaken for those of the next session.
+        self.wait_queue
+            .wait_until(|| (self.device.num_submitted() == 0).then_some(()));
+        self.device.set_callback(Box::new(|| {}));
+        while self.device.pop_reply().is_some() {}
+
+        MOUNTED_TAGS.lock().remove(self.device.tag());
+    }
+}
+
+/// A fid, which refers to a file on the server.
+///
+/// The fid is clunked when it is dropped.
+pub(super) struct Fid {
+    fid: u32,
+    client: Arc<Client>,
+    /// Whether the fid refers to a file, which should be clunked.
+    is_used: bool,
+}
+
+impl Fid {
+    /// Frees the fid without clunking it, which is used if the fid refers to no file.
+    fn forget(mut self) {
+        self.is_used = false;
+    }
+}
+
+impl Drop for Fid {
+    fn drop(&mut self) {
+        // Like Linux, the fid is freed even if the server fails to clunk it.
+        if self.is_used {
+            if let Err(err) = self.client.clunk(self.fid) {
+                debug!("failed to clunk the 9P fid {}: {:?}", self.fid, err);
+            }
+        }
+        self.client.fids.lock().free(self.fid as usize);
+    }
+}
+
+impl Debug for Fid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Fid").field(&self.fid).finish()
+    }
+}
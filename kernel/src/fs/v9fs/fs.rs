@@ -0,0 +1,1032 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::task::Task;
+
+use super::{
+    client::{Client, Fid, MAX_MSIZE, MIN_MSIZE},
+    protocol::{Qid, SetAttr, SetattrValid, Stat, AT_REMOVEDIR, NONUNAME, V9FS_MAGIC},
+};
+use crate::{
+    fs::{
+        device::Device,
+        utils::{
+            AccessMode, CachePage, CreationFlags, DirentVisitor, FileSystem, FsFlags, Inode,
+            InodeMode, InodeType, Metadata, MknodType, PageCache, PageCacheBackend, Permission,
+            SuperBlock, NAME_MAX,
+        },
+    },
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The block size reported if the server does not specify it.
+const BLOCK_SIZE: usize = 4096;
+
+/// The default message size, which is the same as that of Linux.
+const DEFAULT_MSIZE: u32 = 128 * 1024;
+
+/// The mount options of 9P, e.g., `trans=virtio,version=9p2000.L,cache=loose`.
+#[derive(Debug, Clone)]
+pub struct V9fsMountOptions {
+    /// The maximum size of a message, which is negotiated with the server.
+    msize: u32,
+    cache: CacheMode,
+    access: AccessPolicy,
+    /// The user name that is used to attach.
+    uname: String,
+    /// The file tree to attach, which is chosen by the server if it is empty.
+    aname: String,
+}
+
+/// The caching modes, which trade the coherence with the host for the performance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheMode {
+    /// Nothing is cached, so the changes on the host are seen at once.
+    None,
+    /// The file data is cached for `mmap`, but the reads and the writes go to the server.
+    Mmap,
+    /// The file data, the attributes, and the directory entries are cached, and the file data is
+    /// written back lazily.
+    Loose,
+}
+
+/// The policies to check the permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessPolicy {
+    /// The server is attached as the user who mounts the file system, and the permissions are
+    /// checked by the kernel.
+    Client,
+    /// The server is attached as `uname`, and the permissions are checked by the server.
+    Any,
+}
+
+impl V9fsMountOptions {
+    /// Parses the options from the mount data.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self {
+            msize: DEFAULT_MSIZE,
+            cache: CacheMode::None,
+            access: AccessPolicy::Client,
+            uname: "nobody".to_string(),
+            aname: String::new(),
+        };
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "trans" if value != "virtio" => {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "only the virtio transport of 9P is supported"
+                    )
+                }
+                "trans" => (),
+                "version" if value != "9p2000.L" => {
+                    return_errno_with_message!(Errno::EINVAL, "only 9P2000.L is supported")
+                }
+                "version" => (),
+                "msize" => {
+                    let msize = value.parse::<u32>().map_err(|_| {
+                        Error::with_message(Errno::EINVAL, "the 9P message size is invalid")
+                    })?;
+                    if msize < MIN_MSIZE {
+                        return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the 9P message size is too small"
+                        );
+                    }
+                    // Like Linux, the message size is limited by the transport.
+                    options.msize = msize.min(MAX_MSIZE);
+                }
+                "cache" => {
+                    options.cache = match value {
+                        "none" => CacheMode::None,
+                        "mmap" => CacheMode::Mmap,
+                        "loose" => CacheMode::Loose,
+                        "fscache" => return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the 9P cache mode fscache is not supported"
+                        ),
+                        _ => {
+                            return_errno_with_message!(
+                                Errno::EINVAL,
+                                "the 9P cache mode is unknown"
+                            )
+                        }
+                    }
+                }
+                "access" => {
+                    options.access = match value {
+                        "client" => AccessPolicy::Client,
+                        "any" => AccessPolicy::Any,
+                        _ => return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the 9P access policy is not supported"
+                        ),
+                    }
+                }
+                "uname" => options.uname = value.to_string(),
+                "aname" => options.aname = value.to_string(),
+                _ => return_errno_with_message!(Errno::EINVAL, "the 9P mount option is unknown"),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Returns the maximum size of a message.
+    pub fn msize(&self) -> u32 {
+        self.msize
+    }
+}
+
+/// A file system that is served by a 9P server over a virtio-9p device.
+pub struct V9fs {
+    root: Arc<V9fsInode>,
+    client: Arc<Client>,
+    options: V9fsMountOptions,
+    /// The inodes that are alive, indexed by the paths of the qids.
+    inodes: Mutex<BTreeMap<u64, Weak<V9fsInode>>>,
+}
+
+impl V9fs {
+    /// Attaches to the server of the virtio-9p device with the tag.
+    pub fn new(tag: &str, options: V9fsMountOptions) -> Result<Arc<Self>> {
+        let client = Client::connect(tag, options.msize)?;
+
+        let n_uname = match options.access {
+            AccessPolicy::Client => current_fsuid(),
+            AccessPolicy::Any => NONUNAME,
+        };
+        let (root_fid, _) = client.attach(&options.uname, &options.aname, n_uname)?;
+        let root_stat = client.getattr(&root_fid)?;
+        if InodeType::from_raw_mode(root_stat.mode as u16)? != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the 9P root is not a directory");
+        }
+
+        Ok(Arc::new_cyclic(|weak_fs| Self {
+            root: V9fsInode::new(
+                root_fid,
+                &root_stat,
+                client.clone(),
+                options.cache,
+                weak_fs.clone(),
+            ),
+            client,
+            options,
+            inodes: Mutex::new(BTreeMap::new()),
+        }))
+    }
+
+    /// Returns the inode of the file that the fid refers to.
+    fn instantiate(&self, fid: Fid, stat: &Stat) -> Result<Arc<V9fsInode>> {
+        if stat.qid.path == self.root.qid.path {
+            self.root.update_attr(stat);
+            return Ok(self.root.clone());
+        }
+
+        let mut inodes = self.inodes.lock();
+        // The fid is clunked if there is an inode of the same file.
+        if let Some(inode) = inodes.get(&stat.qid.path).and_then(Weak::upgrade) {
+            drop(inodes);
+            inode.update_attr(stat);
+            return Ok(inode);
+        }
+
+        let inode = V9fsInode::new(
+            fid,
+            stat,
+            self.client.clone(),
+            self.options.cache,
+            self.root.fs.clone(),
+        );
+        inodes.insert(stat.qid.path, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    fn statfs(&self) -> Result<SuperBlock> {
+        let st = self.client.statfs(&self.root.fid)?;
+
+        let mut sb = SuperBlock::new(V9FS_MAGIC, st.bsize as usize, st.namelen as usize);
+        sb.blocks = st.blocks as usize;
+        sb.bfree = st.bfree as usize;
+        sb.bavail = st.bavail as usize;
+        sb.files = st.files as usize;
+        sb.ffree = st.ffree as usize;
+        Ok(sb)
+    }
+}
+
+impl FileSystem for V9fs {
+    fn sync(&self) -> Result<()> {
+        if self.options.cache == CacheMode::None {
+            return Ok(());
+        }
+
+        let inodes: Vec<Arc<V9fsInode>> = self
+            .inodes
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for inode in inodes.iter() {
+            inode.flush()?;
+        }
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.statfs()
+            .unwrap_or_else(|_| SuperBlock::new(V9FS_MAGIC, BLOCK_SIZE, NAME_MAX))
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+/// An inode of a file in a 9P file system.
+pub struct V9fsInode {
+    /// The fid that refers to the file, which is never opened.
+    fid: Arc<Fid>,
+    qid: Qid,
+    type_: InodeType,
+    cache: CacheMode,
+    attr: SpinLock<CachedAttr>,
+    data: Arc<FileData>,
+    /// The cached pages of a regular file, which are absent if the cache mode is `none`.
+    page_cache: Option<PageCache>,
+    dir_cursor: Mutex<DirCursor>,
+    client: Arc<Client>,
+    fs: Weak<V9fs>,
+}
+
+struct CachedAttr {
+    stat: Stat,
+    /// Whether the attributes can be used without being fetched again.
+    is_valid: bool,
+}
+
+/// The data of a file, which is read and written with the opened fids.
+///
+/// This is the backend of the page cache. It is separated from the inode so that the dirty
+/// pages can be written back when the inode is dropped.
+struct FileData {
+    fid: Arc<Fid>,
+    handles: Mutex<FileHandles>,
+    /// The size of the file, which limits the pages that are written back.
+    size: AtomicUsize,
+    client: Arc<Client>,
+}
+
+/// The fids that are opened on demand.
+///
+/// Since there are no per-file operations in the VFS, the fids are shared by the opened files of
+/// the inode, and they are clunked when the inode is dropped.
+#[derive(Default)]
+struct FileHandles {
+    read: Option<Arc<OpenFid>>,
+    write: Option<Arc<OpenFid>>,
+}
+
+struct OpenFid {
+    fid: Fid,
+    /// The maximum size of the data in a message, which is chosen by the server if it is not
+    /// zero.
+    iounit: u32,
+}
+
+/// The position after the last `readdir_at`.
+///
+/// The offsets in the 9P directories are cookies that are chosen by the server, so the cookie
+/// of the next entry is remembered to continue reading the directory.
+#[derive(Default)]
+struct DirCursor {
+    offset: usize,
+    cookie: u64,
+}
+
+impl V9fsInode {
+    fn new(
+        fid: Fid,
+        stat: &Stat,
+        client: Arc<Client>,
+        cache: CacheMode,
+        fs: Weak<V9fs>,
+    ) -> Arc<Self> {
+        let type_ = InodeType::from_raw_mode(stat.mode as u16).unwrap_or(InodeType::File);
+        let fid = Arc::new(fid);
+        let data = Arc::new(FileData {
+            fid: fid.clone(),
+            handles: Mutex::new(FileHandles::default()),
+            size: AtomicUsize::new(stat.size as usize),
+            client: client.clone(),
+        });
+        let page_cache = (type_ == InodeType::File && cache != CacheMode::None).then(|| {
+            PageCache::with_capacity(stat.size as usize, Arc::downgrade(&data) as _).unwrap()
+        });
+
+        Arc::new(Self {
+            fid,
+            qid: stat.qid,
+            type_,
+            cache,
+            attr: SpinLock::new(CachedAttr {
+                stat: *stat,
+                is_valid: true,
+            }),
+            data,
+            page_cache,
+            dir_cursor: Mutex::new(DirCursor::default()),
+            client,
+            fs,
+        })
+    }
+
+    fn fs(&self) -> Arc<V9fs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the 9P inode is not a directory");
+        }
+        Ok(())
+    }
+
+    fn check_not_dir(&self) -> Result<()> {
+        if self.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "the 9P inode is a directory");
+        }
+        Ok(())
+    }
+
+    /// Returns whether the file data is cached and written back lazily.
+    fn is_write_back(&self) -> bool {
+        self.cache == CacheMode::Loose && self.page_cache.is_some()
+    }
+
+    fn update_attr(&self, stat: &Stat) {
+        let mut stat = *stat;
+        if self.is_write_back() {
+            // The size of the server is stale if there are dirty pages.
+            stat.size = self.data.size() as u64;
+        } else {
+            let old_size = self.data.size.swap(stat.size as usize, Ordering::Relaxed);
+            if let Some(page_cache) = self.page_cache.as_ref() {
+                if old_size != stat.size as usize {
+                    let _ = page_cache.resize(stat.size as usize);
+                }
+            }
+        }
+
+        *self.attr.lock() = CachedAttr {
+            stat,
+            is_valid: self.cache == CacheMode::Loose,
+        };
+    }
+
+    fn invalidate_attr(&self) {
+        self.attr.lock().is_valid = false;
+    }
+
+    fn getattr(&self) -> Result<Stat> {
+        {
+            let cached = self.attr.lock();
+            if cached.is_valid {
+                return Ok(cached.stat);
+            }
+        }
+
+        let stat = self.client.getattr(&self.fid)?;
+        self.update_attr(&stat);
+        Ok(self.attr.lock().stat)
+    }
+
+    /// Returns the attributes, which are the cached ones if the server fails to reply.
+    fn attr(&self) -> Stat {
+        self.getattr().unwrap_or_else(|err| {
+            debug!("failed to get the 9P attributes: {:?}", err);
+            self.attr.lock().stat
+        })
+    }
+
+    fn setattr(&self, attr: &SetAttr) -> Result<()> {
+        self.client.setattr(&self.fid, attr)?;
+        self.invalidate_attr();
+        Ok(())
+    }
+
+    fn set_time(&self, valid: SetattrValid, time: Duration) {
+        let mut attr = SetAttr::new(valid);
+        attr.atime = time;
+        attr.mtime = time;
+        if let Err(err) = self.setattr(&attr) {
+            debug!("failed to set the 9P times: {:?}", err);
+        }
+    }
+
+    /// Returns the inode of the entry with the name.
+    fn lookup_inode(&self, name: &str) -> Result<Arc<V9fsInode>> {
+        let fid = self.client.walk(&self.fid, &[name])?;
+        let stat = self.client.getattr(&fid)?;
+        self.fs().instantiate(fid, &stat)
+    }
+
+    /// Casts the inode to a `V9fsInode` in the same file system.
+    fn same_fs_inode<'a>(&self, inode: &'a Arc<dyn Inode>) -> Result<&'a V9fsInode> {
+        inode
+            .downcast_ref::<V9fsInode>()
+            .filter(|inode| Weak::ptr_eq(&inode.fs, &self.fs))
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not same fs"))
+    }
+
+    fn create_file(&self, name: &str, mode: u32) -> Result<Arc<V9fsInode>> {
+        // `Tlcreate` changes the fid to refer to the new file, so the directory fid is cloned.
+        let fid = self.client.walk(&self.fid, &[])?;
+        let flags =
+            AccessMode::O_RDWR as u32 | (CreationFlags::O_CREAT | CreationFlags::O_EXCL).bits();
+        let iounit = self
+            .client
+            .lcreate(&fid, name, flags, mode, current_fsgid())?;
+        self.invalidate_attr();
+
+        let inode = self.lookup_inode(name)?;
+        // The opened fid is reused, which saves an open if the new file is written.
+        let open_fid = Arc::new(OpenFid { fid, iounit });
+        let mut handles = inode.data.handles.lock();
+        handles.read.get_or_insert_with(|| open_fid.clone());
+        handles.write.get_or_insert(open_fid);
+        drop(handles);
+        Ok(inode)
+    }
+
+    /// Writes back the dirty pages and syncs the file on the server.
+    fn flush(&self) -> Result<()> {
+        if let Some(page_cache) = self.page_cache.as_ref() {
+            page_cache.evict_range(0..self.data.size())?;
+        }
+        Ok(())
+    }
+
+    fn fsync(&self, is_datasync: bool) -> Result<()> {
+        self.flush()?;
+
+        let Some(open_fid) = self.data.handles.lock().write.clone() else {
+            return Ok(());
+        };
+        self.client.fsync(&open_fid.fid, is_datasync)
+    }
+
+    /// Updates the size after the data is written at `end`.
+    fn extend_size(&self, end: usize) -> Result<()> {
+        let old_size = self.data.size.fetch_max(end, Ordering::Relaxed);
+        if end > old_size {
+            if let Some(page_cache) = self.page_cache.as_ref() {
+                page_cache.resize(end)?;
+            }
+        }
+
+        let mut cached = self.attr.lock();
+        cached.stat.size = cached.stat.size.max(end as u64);
+        // The times are updated by the server when the data is written.
+        if !self.is_write_back() {
+            cached.is_valid = false;
+        }
+        Ok(())
+    }
+
+    fn readdir_with_fid(
+        &self,
+        open_fid: &Fid,
+        offset: usize,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<usize> {
+        let mut cursor = self.dir_cursor.lock();
+        // The directory is read from the start unless the last read is continued.
+        let (mut index, mut cookie) = if offset != 0 && cursor.offset == offset {
+            (cursor.offset, cursor.cookie)
+        } else {
+            (0, 0)
+        };
+        let mut count = 0;
+
+        'read: loop {
+            let entries = self.client.readdir(open_fid, cookie)?;
+            if entries.is_empty() {
+                break;
+            }
+
+            for dirent in entries.iter() {
+                if index >= offset {
+                    let type_ = InodeType::from_raw_mode((dirent.type_ as u16) << 12)
+                        .unwrap_or(InodeType::File);
+                    if let Err(err) = visitor.visit(&dirent.name, dirent.qid.path, type_, index) {
+                        if count == 0 {
+                            return Err(err);
+                        }
+                        break 'read;
+                    }
+                    count += 1;
+                }
+                index += 1;
+                cookie = dirent.offset;
+            }
+        }
+
+        *cursor = DirCursor {
+            offset: offset + count,
+            cookie,
+        };
+        Ok(count)
+    }
+}
+
+impl Drop for V9fsInode {
+    fn drop(&mut self) {
+        if self.is_write_back() {
+            if let Err(err) = self.flush() {
+                warn!("failed to write back the 9P file: {:?}", err);
+            }
+        }
+
+        if let Some(fs) = self.fs.upgrade() {
+            let mut inodes = fs.inodes.lock();
+            // The entry may be replaced by a new inode of the same file.
+            if inodes
+                .get(&self.qid.path)
+                .is_some_and(|inode| core::ptr::eq(inode.as_ptr(), self))
+            {
+                inodes.remove(&self.qid.path);
+            }
+        }
+    }
+}
+
+impl FileData {
+    fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Returns the opened fid for reading or writing, which is opened if necessary.
+    fn open_fid(&self, is_write: bool) -> Result<Arc<OpenFid>> {
+        let mut handles = self.handles.lock();
+        let handle = if is_write {
+            &mut handles.write
+        } else {
+            &mut handles.read
+        };
+        if let Some(open_fid) = handle.as_ref() {
+            return Ok(open_fid.clone());
+        }
+
+        let flags = if is_write {
+            AccessMode::O_WRONLY
+        } else {
+            AccessMode::O_RDONLY
+        };
+        let fid = self.client.walk(&self.fid, &[])?;
+        let iounit = self.client.lopen(&fid, flags as u32)?;
+        let open_fid = Arc::new(OpenFid { fid, iounit });
+        *handle = Some(open_fid.clone());
+        Ok(open_fid)
+    }
+
+    fn max_io_size(&self, open_fid: &OpenFid) -> usize {
+        let max_io_size = self.client.max_io_size();
+        if open_fid.iounit == 0 {
+            max_io_size
+        } else {
+            max_io_size.min(open_fid.iounit as usize)
+        }
+    }
+
+    fn read(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let open_fid = self.open_fid(false)?;
+        let max_io_size = self.max_io_size(&open_fid);
+
+        let mut read_len = 0;
+        while writer.has_avail() {
+            let size = writer.avail().min(max_io_size);
+            let data = self
+                .client
+                .read(&open_fid.fid, (offset + read_len) as u64, size)?;
+            writer.write_fallible(&mut VmReader::from(data.as_slice()))?;
+            read_len += data.len();
+            if data.len() < size {
+                break;
+            }
+        }
+        Ok(read_len)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let open_fid = self.open_fid(true)?;
+        let max_io_size = self.max_io_size(&open_fid);
+
+        let mut write_len = 0;
+        while reader.has_remain() {
+            let mut data = vec![0; reader.remain().min(max_io_size)];
+            reader.read_fallible(&mut VmWriter::from(data.as_mut_slice()))?;
+
+            let written = self
+                .client
+                .write(&open_fid.fid, (offset + write_len) as u64, &data)?;
+            write_len += written;
+            if written < data.len() {
+                break;
+            }
+        }
+        Ok(write_len)
+    }
+}
+
+impl PageCacheBackend for FileData {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        self.read(
+            idx * PAGE_SIZE,
+            &mut VmWriter::from(buf.as_mut_slice()).to_fallible(),
+        )?;
+        frame.writer().write(&mut VmReader::from(buf.as_slice()));
+        // The page is read synchronously.
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let offset = idx * PAGE_SIZE;
+        let len = self.size().saturating_sub(offset).min(PAGE_SIZE);
+        if len == 0 {
+            return Ok(BioWaiter::new());
+        }
+
+        let mut buf = vec![0u8; len];
+        frame.reader().read(&mut VmWriter::from(buf.as_mut_slice()));
+        let written = self.write(offset, &mut VmReader::from(buf.as_slice()).to_fallible())?;
+        if written < len {
+            return_errno_with_message!(Errno::EIO, "the 9P server writes a partial page");
+        }
+        // The page is written synchronously.
+        Ok(BioWaiter::new())
+    }
+
+    fn npages(&self) -> usize {
+        self.size().div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Inode for V9fsInode {
+    fn size(&self) -> usize {
+        self.attr().size as usize
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        self.check_not_dir()?;
+
+        let mut attr = SetAttr::new(SetattrValid::SIZE);
+        attr.size = new_size as u64;
+        self.client.setattr(&self.fid, &attr)?;
+
+        self.data.size.store(new_size, Ordering::Relaxed);
+        if let Some(page_cache) = self.page_cache.as_ref() {
+            page_cache.resize(new_size)?;
+        }
+        let mut cached = self.attr.lock();
+        cached.stat.size = new_size as u64;
+        cached.is_valid = false;
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let stat = self.attr();
+        Metadata {
+            dev: 0,
+            ino: stat.qid.path,
+            size: stat.size as usize,
+            blk_size: if stat.blksize != 0 {
+                stat.blksize as usize
+            } else {
+                BLOCK_SIZE
+            },
+            blocks: stat.blocks as usize,
+            atime: stat.atime,
+            mtime: stat.mtime,
+            ctime: stat.ctime,
+            type_: self.type_,
+            mode: InodeMode::from_bits_truncate(stat.mode as u16),
+            nlinks: stat.nlink as usize,
+            uid: Uid::new(stat.uid),
+            gid: Gid::new(stat.gid),
+            rdev: stat.rdev,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.qid.path
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(InodeMode::from_bits_truncate(self.getattr()?.mode as u16))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        let mut attr = SetAttr::new(SetattrValid::MODE);
+        attr.mode = self.type_ as u32 | mode.bits() as u32;
+        self.setattr(&attr)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.getattr()?.uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        let mut attr = SetAttr::new(SetattrValid::UID);
+        attr.uid = uid.into();
+        self.setattr(&attr)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.getattr()?.gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        let mut attr = SetAttr::new(SetattrValid::GID);
+        attr.gid = gid.into();
+        self.setattr(&attr)
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(SetattrValid::ATIME | SetattrValid::ATIME_SET, time);
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(SetattrValid::MTIME | SetattrValid::MTIME_SET, time);
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata().ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {
+        // Like Linux, the change time is maintained by the server.
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.check_not_dir()?;
+
+        let Some(page_cache) = self.page_cache.as_ref().filter(|_| self.is_write_back()) else {
+            return self.read_direct_at(offset, writer);
+        };
+
+        let (read_off, read_len) = {
+            let file_size = self.data.size();
+            let start = file_size.min(offset);
+            let end = file_size.min(offset.saturating_add(writer.avail()));
+            (start, end - start)
+        };
+        page_cache.pages().read(read_off, writer.limit(read_len))?;
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.check_not_dir()?;
+
+        // The data that is written by `mmap` is written back before it is read.
+        if let Some(page_cache) = self.page_cache.as_ref() {
+            page_cache.evict_range(offset..offset + writer.avail())?;
+        }
+        self.data.read(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.check_not_dir()?;
+
+        let Some(page_cache) = self.page_cache.as_ref().filter(|_| self.is_write_back()) else {
+            return self.write_direct_at(offset, reader);
+        };
+
+        let write_len = reader.remain();
+        self.extend_size(offset + write_len)?;
+        page_cache.pages().write(offset, reader)?;
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.check_not_dir()?;
+
+        // The cached pages are written back and discarded, so they are read again.
+        if let Some(page_cache) = self.page_cache.as_ref() {
+            let range = offset..offset + reader.remain();
+            page_cache.evict_range(range.clone())?;
+            page_cache.discard_range(range);
+        }
+
+        let write_len = self.data.write(offset, reader)?;
+        self.extend_size(offset + write_len)?;
+        Ok(write_len)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let mode = type_ as u32 | mode.bits() as u32;
+        let inode = match type_ {
+            InodeType::File => self.create_file(name, mode)?,
+            InodeType::Dir => {
+                self.client.mkdir(&self.fid, name, mode, current_fsgid())?;
+                self.invalidate_attr();
+                self.lookup_inode(name)?
+            }
+            InodeType::SymLink => {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the 9P symlinks must be created with the targets"
+                );
+            }
+            _ => {
+                self.client
+                    .mknod(&self.fid, name, mode, (0, 0), current_fsgid())?;
+                self.invalidate_attr();
+                self.lookup_inode(name)?
+            }
+        };
+        Ok(inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let device_id = match &type_ {
+            MknodType::CharDeviceNode(device) | MknodType::BlockDeviceNode(device) => {
+                let id = device.id();
+                (id.major(), id.minor())
+            }
+            MknodType::NamedPipeNode => (0, 0),
+        };
+        let mode = type_.inode_type() as u32 | mode.bits() as u32;
+        self.client
+            .mknod(&self.fid, name, mode, device_id, current_fsgid())?;
+        self.invalidate_attr();
+
+        let inode = self.lookup_inode(name)?;
+        Ok(inode)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        if !self.type_.is_device() {
+            return None;
+        }
+        crate::device::get_device(self.attr().rdev as usize).ok()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        let open_fid = self.client.walk(&self.fid, &[])?;
+        self.client.lopen(
+            &open_fid,
+            AccessMode::O_RDONLY as u32 | CreationFlags::O_DIRECTORY.bits(),
+        )?;
+        self.readdir_with_fid(&open_fid, offset, visitor)
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let old = self.same_fs_inode(old)?;
+
+        self.client.link(&self.fid, &old.fid, name)?;
+        self.invalidate_attr();
+        old.invalidate_attr();
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.client.unlinkat(&self.fid, name, 0)?;
+        self.invalidate_attr();
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+
+        self.client.unlinkat(&self.fid, name, AT_REMOVEDIR)?;
+        self.invalidate_attr();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let inode = self.lookup_inode(name)?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        self.check_dir()?;
+        let target = self.same_fs_inode(target)?;
+        target.check_dir()?;
+
+        self.client
+            .renameat(&self.fid, old_name, &target.fid, new_name)?;
+        self.invalidate_attr();
+        target.invalidate_attr();
+        Ok(())
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        self.client
+            .symlink(&self.fid, name, target, current_fsgid())?;
+        self.invalidate_attr();
+
+        let inode = self.lookup_inode(name)?;
+        Ok(inode)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.type_ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "the 9P inode is not a symlink");
+        }
+
+        self.client.readlink(&self.fid)
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the 9P symlinks cannot be changed");
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fsync(false)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fsync(true)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        // The files can be changed on the host, so they are looked up again unless the cache
+        // mode is `loose`.
+        self.cache == CacheMode::Loose
+    }
+
+    fn check_permission(&self, perm: Permission) -> Result<()> {
+        match self.fs().options.access {
+            AccessPolicy::Client => self.metadata().check_permission(perm),
+            AccessPolicy::Any => Ok(()),
+        }
+    }
+}
+
+fn current_credentials_ids() -> Option<(Uid, Gid)> {
+    Task::current().and_then(|task| {
+        task.as_posix_thread().map(|posix_thread| {
+            let credentials = posix_thread.credentials();
+            (credentials.fsuid(), credentials.fsgid())
+        })
+    })
+}
+
+fn current_fsuid() -> u32 {
+    current_credentials_ids().map_or(0, |(uid, _)| uid.into())
+}
+
+fn current_fsgid() -> u32 {
+    current_credentials_ids().map_or(0, |(_, gid)| gid.into())
+}
@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The 9P file system (9pfs).
+//!
+//! A 9P server (e.g., the one in QEMU) shares a host directory over a virtio-9p device, which is
+//! mounted as a `9p` file system with the tag of the device as the source, e.g.,
+//! `mount -t 9p -o trans=virtio,version=9p2000.L hostshare /mnt`.
+//!
+//! The features are as follows:
+//! 1. The protocol of 9P2000.L, including the negotiation of the message size with `Tversion`.
+//! 2. The operations on the files, the directories, and the symlinks, e.g., `Twalk`,
+//!    `Tgetattr`, `Tread`, `Twrite`, `Tlcreate`, and `Trenameat`.
+//! 3. Managing the fids, which are clunked when the inodes are dropped.
+//! 4. The caching modes of Linux, i.e., `cache=none`, `cache=mmap`, and `cache=loose`.
+//! 5. The access policies `access=client` and `access=any`.
+//!
+//! # Limitation
+//!
+//! Here we summarizes the features that need to be implemented in the future.
+//! 1. Supports the transports other than virtio, e.g., TCP.
+//! 2. Attaches for each user, which is `access=user` of Linux and is its default policy.
+//! 3. Supports the extended attributes, the file locks, and the POSIX ACLs.
+//! 4. Flushes the requests with `Tflush` when the waiting threads are interrupted.
+
+pub use fs::{V9fs, V9fsMountOptions};
+
+mod client;
+mod fs;
+mod protocol;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::{protocol::*, *};
+    use crate::prelude::*;
+
+    #[ktest]
+    fn encode_and_decode() {
+        let request = Encoder::new(MsgType::Twalk)
+            .u32(1)
+            .u32(2)
+            .u16(1)
+            .str("hello")
+            .finish(3);
+        assert_eq!(
+            request,
+            [
+                24, 0, 0, 0, 110, 3, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 5, 0, b'h', b'e', b'l', b'l',
+                b'o'
+            ]
+        );
+
+        let mut reply = vec![0, 0, 0, 0, MsgType::Twalk as u8 + 1, 3, 0, 1, 0];
+        reply.extend_from_slice(&[0x80, 7, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0]);
+        let size = reply.len() as u32;
+        reply[..4].copy_from_slice(&size.to_le_bytes());
+        assert_eq!(reply_tag(&reply), Some(3));
+
+        let (tag, body) = parse_reply(&reply, MsgType::Twalk).unwrap();
+        assert_eq!(tag, 3);
+        let mut decoder = Decoder::new(body);
+        assert_eq!(decoder.u16().unwrap(), 1);
+        assert_eq!(
+            decoder.qid().unwrap(),
+            Qid {
+                type_: 0x80,
+                version: 7,
+                path: 42,
+            }
+        );
+        assert!(decoder.is_empty());
+        assert_eq!(decoder.u8().unwrap_err().error(), Errno::EIO);
+    }
+
+    #[ktest]
+    fn parse_error_reply() {
+        let reply = [11, 0, 0, 0, MsgType::Rlerror as u8, 3, 0, 2, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&reply, MsgType::Twalk).unwrap_err().error(),
+            Errno::ENOENT
+        );
+
+        let reply = [7, 0, 0, 0, MsgType::Tread as u8 + 1, 3, 0];
+        assert_eq!(
+            parse_reply(&reply, MsgType::Twalk).unwrap_err().error(),
+            Errno::EIO
+        );
+    }
+
+    #[ktest]
+    fn parse_mount_options() {
+        let options =
+            V9fsMountOptions::parse("trans=virtio,version=9p2000.L,msize=1048576,cache=loose")
+                .unwrap();
+        assert_eq!(options.msize(), 512 * 1024);
+
+        for data in [
+            "trans=tcp",
+            "version=9p2000.u",
+            "msize=1024",
+            "cache=fscache",
+            "access=user",
+            "unknown",
+        ] {
+            assert_eq!(
+                V9fsMountOptions::parse(data).unwrap_err().error(),
+                Errno::EINVAL
+            );
+        }
+    }
+}
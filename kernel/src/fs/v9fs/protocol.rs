@@ -0,0 +1,395 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The messages of the 9P2000.L protocol.
+//!
+//! A message starts with a header of `size[4] type[1] tag[2]`, which is followed by the fields
+//! of the type. All the integers are little-endian, and a string is encoded as `len[2]` followed
+//! by `len` bytes without the NUL terminator.
+//!
+//! Reference: <https://github.com/chaos/diod/blob/master/protocol.md>
+
+use core::time::Duration;
+
+use crate::prelude::*;
+
+/// The version of the protocol.
+pub(super) const VERSION_9P2000_L: &str = "9P2000.L";
+
+/// The tag of `Tversion`, which is not used by the other messages.
+pub(super) const NOTAG: u16 = !0;
+
+/// The fid that means no fid, e.g., as the `afid` of `Tattach` without authentication.
+pub(super) const NOFID: u32 = !0;
+
+/// The numeric user name that means no user, so the user is named by the string.
+pub(super) const NONUNAME: u32 = !0;
+
+/// The size of the message header.
+pub(super) const HEADER_SIZE: usize = 7;
+
+/// The size of the headers of `Tread`, `Twrite`, `Rread`, and `Rwrite`, which limits the size of
+/// the data in a message.
+pub(super) const IOHDRSZ: usize = 24;
+
+/// The size of the header of `Rreaddir`.
+pub(super) const READDIRHDRSZ: usize = 24;
+
+/// The magic number of 9P file systems.
+pub(super) const V9FS_MAGIC: u64 = 0x0102_1997;
+
+/// The flag of `Tunlinkat` that removes a directory.
+pub(super) const AT_REMOVEDIR: u32 = 0x200;
+
+/// The message types of 9P2000.L.
+///
+/// The type of the reply to a request is the type of the request plus one, except that an error
+/// is replied with `Rlerror`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u8)]
+pub(super) enum MsgType {
+    Rlerror = 7,
+    Tstatfs = 8,
+    Tlopen = 12,
+    Tlcreate = 14,
+    Tsymlink = 16,
+    Tmknod = 18,
+    Treadlink = 22,
+    Tgetattr = 24,
+    Tsetattr = 26,
+    Treaddir = 40,
+    Tfsync = 50,
+    Tlink = 70,
+    Tmkdir = 72,
+    Trenameat = 74,
+    Tunlinkat = 76,
+    Tversion = 100,
+    Tattach = 104,
+    Twalk = 110,
+    Tread = 116,
+    Twrite = 118,
+    Tclunk = 120,
+}
+
+bitflags! {
+    /// The attributes that are requested by `Tgetattr`.
+    pub(super) struct GetattrMask: u64 {
+        const MODE         = 0x0000_0001;
+        const NLINK        = 0x0000_0002;
+        const UID          = 0x0000_0004;
+        const GID          = 0x0000_0008;
+        const RDEV         = 0x0000_0010;
+        const ATIME        = 0x0000_0020;
+        const MTIME        = 0x0000_0040;
+        const CTIME        = 0x0000_0080;
+        const INO          = 0x0000_0100;
+        const SIZE         = 0x0000_0200;
+        const BLOCKS       = 0x0000_0400;
+        /// The attributes of `stat`.
+        const BASIC        = 0x0000_07ff;
+    }
+}
+
+bitflags! {
+    /// The attributes that are set by `Tsetattr`.
+    pub(super) struct SetattrValid: u32 {
+        const MODE      = 0x0000_0001;
+        const UID       = 0x0000_0002;
+        const GID       = 0x0000_0004;
+        const SIZE      = 0x0000_0008;
+        const ATIME     = 0x0000_0010;
+        const MTIME     = 0x0000_0020;
+        const CTIME     = 0x0000_0040;
+        /// The access time is set to the given time instead of the current time.
+        const ATIME_SET = 0x0000_0080;
+        /// The modification time is set to the given time instead of the current time.
+        const MTIME_SET = 0x0000_0100;
+    }
+}
+
+/// The unique identification of a file on the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Qid {
+    pub(super) type_: u8,
+    pub(super) version: u32,
+    /// The number that is unique among the files of the server, which is used as the inode
+    /// number.
+    pub(super) path: u64,
+}
+
+impl Qid {
+    /// The size of a qid in a message.
+    pub(super) const SIZE: usize = 13;
+}
+
+/// The attributes in `Rgetattr`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Stat {
+    pub(super) qid: Qid,
+    pub(super) mode: u32,
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+    pub(super) nlink: u64,
+    pub(super) rdev: u64,
+    pub(super) size: u64,
+    pub(super) blksize: u64,
+    pub(super) blocks: u64,
+    pub(super) atime: Duration,
+    pub(super) mtime: Duration,
+    pub(super) ctime: Duration,
+}
+
+/// The attributes of the file system in `Rstatfs`.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct StatFs {
+    pub(super) bsize: u32,
+    pub(super) blocks: u64,
+    pub(super) bfree: u64,
+    pub(super) bavail: u64,
+    pub(super) files: u64,
+    pub(super) ffree: u64,
+    pub(super) namelen: u32,
+}
+
+/// The attributes that are set by `Tsetattr`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SetAttr {
+    pub(super) valid: SetattrValid,
+    pub(super) mode: u32,
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+    pub(super) size: u64,
+    pub(super) atime: Duration,
+    pub(super) mtime: Duration,
+}
+
+impl SetAttr {
+    pub(super) fn new(valid: SetattrValid) -> Self {
+        Self {
+            valid,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+        }
+    }
+}
+
+/// A directory entry in `Rreaddir`.
+#[derive(Debug, Clone)]
+pub(super) struct Dirent {
+    pub(super) qid: Qid,
+    /// The offset of the next entry.
+    pub(super) offset: u64,
+    /// The type of the entry, which is one of the `DT_*` values.
+    pub(super) type_: u8,
+    pub(super) name: String,
+}
+
+/// The encoder of a request.
+pub(super) struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    /// Creates a request of the type, whose header is filled by [`Self::finish`].
+    pub(super) fn new(type_: MsgType) -> Self {
+        let mut buf = Vec::with_capacity(PAGE_SIZE);
+        buf.extend_from_slice(&[0; HEADER_SIZE]);
+        buf[4] = type_ as u8;
+        Self { buf }
+    }
+
+    pub(super) fn u8(mut self, value: u8) -> Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub(super) fn u16(mut self, value: u16) -> Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(super) fn u32(mut self, value: u32) -> Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(super) fn u64(mut self, value: u64) -> Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(super) fn str(self, value: &str) -> Self {
+        let this = self.u16(value.len() as u16);
+        this.bytes(value.as_bytes())
+    }
+
+    pub(super) fn bytes(mut self, value: &[u8]) -> Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub(super) fn time(self, value: Duration) -> Self {
+        self.u64(value.as_secs()).u64(value.subsec_nanos() as u64)
+    }
+
+    /// Returns the type of the request.
+    pub(super) fn type_(&self) -> MsgType {
+        MsgType::try_from(self.buf[4]).unwrap()
+    }
+
+    /// Returns the encoded request with the tag.
+    pub(super) fn finish(mut self, tag: u16) -> Vec<u8> {
+        let size = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&size.to_le_bytes());
+        self.buf[5..7].copy_from_slice(&tag.to_le_bytes());
+        self.buf
+    }
+}
+
+/// The decoder of a reply.
+pub(super) struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub(super) fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub(super) fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return_errno_with_message!(Errno::EIO, "the 9P message is truncated");
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(super) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(super) fn str(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len)?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    pub(super) fn time(&mut self) -> Result<Duration> {
+        let secs = self.u64()?;
+        let nsecs = self.u64()?;
+        Ok(Duration::from_secs(secs).saturating_add(Duration::from_nanos(nsecs)))
+    }
+
+    pub(super) fn qid(&mut self) -> Result<Qid> {
+        Ok(Qid {
+            type_: self.u8()?,
+            version: self.u32()?,
+            path: self.u64()?,
+        })
+    }
+
+    /// Decodes the body of `Rgetattr`.
+    pub(super) fn stat(&mut self) -> Result<Stat> {
+        let _valid = self.u64()?;
+        let stat = Stat {
+            qid: self.qid()?,
+            mode: self.u32()?,
+            uid: self.u32()?,
+            gid: self.u32()?,
+            nlink: self.u64()?,
+            rdev: self.u64()?,
+            size: self.u64()?,
+            blksize: self.u64()?,
+            blocks: self.u64()?,
+            atime: self.time()?,
+            mtime: self.time()?,
+            ctime: self.time()?,
+        };
+        // The birth time, the generation, and the data version are not used.
+        Ok(stat)
+    }
+
+    /// Decodes the body of `Rstatfs`.
+    pub(super) fn statfs(&mut self) -> Result<StatFs> {
+        let _type = self.u32()?;
+        let bsize = self.u32()?;
+        let blocks = self.u64()?;
+        let bfree = self.u64()?;
+        let bavail = self.u64()?;
+        let files = self.u64()?;
+        let ffree = self.u64()?;
+        let _fsid = self.u64()?;
+        let namelen = self.u32()?;
+        Ok(StatFs {
+            bsize,
+            blocks,
+            bfree,
+            bavail,
+            files,
+            ffree,
+            namelen,
+        })
+    }
+
+    /// Decodes a directory entry in the data of `Rreaddir`.
+    pub(super) fn dirent(&mut self) -> Result<Dirent> {
+        Ok(Dirent {
+            qid: self.qid()?,
+            offset: self.u64()?,
+            type_: self.u8()?,
+            name: self.str()?,
+        })
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+/// Splits a reply into the header and the body.
+///
+/// The errors in `Rlerror` are returned as the errors.
+pub(super) fn parse_reply(reply: &[u8], request_type: MsgType) -> Result<(u16, &[u8])> {
+    let mut decoder = Decoder::new(reply);
+    let size = decoder.u32()? as usize;
+    let type_ = decoder.u8()?;
+    let tag = decoder.u16()?;
+    if size < HEADER_SIZE || size > reply.len() {
+        return_errno_with_message!(Errno::EIO, "the 9P message size is invalid");
+    }
+    let body = &reply[HEADER_SIZE..size];
+
+    if type_ == MsgType::Rlerror as u8 {
+        let ecode = Decoder::new(body).u32()?;
+        let errno = Errno::try_from(ecode as i32).unwrap_or(Errno::EIO);
+        return_errno_with_message!(errno, "the 9P server replies with an error");
+    }
+    if type_ != request_type as u8 + 1 {
+        return_errno_with_message!(Errno::EIO, "the 9P reply type is unexpected");
+    }
+
+    Ok((tag, body))
+}
+
+/// Returns the tag of a reply, which is used to find the request.
+pub(super) fn reply_tag(reply: &[u8]) -> Option<u16> {
+    reply
+        .get(5..HEADER_SIZE)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The OEM code pages, in which the short names are encoded.
+
+use crate::prelude::*;
+
+/// An OEM code page.
+///
+/// The lower half of a code page is ASCII. Only the upper half is defined by the tables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) enum Codepage {
+    /// The code page of the original IBM PC, which is the default one.
+    #[default]
+    Cp437,
+    /// The Western European code page.
+    Cp850,
+}
+
+impl Codepage {
+    /// Parses the code page from the number in the mount option, e.g., `codepage=437`.
+    pub(super) fn from_number(number: &str) -> Result<Self> {
+        match number {
+            "437" => Ok(Self::Cp437),
+            "850" => Ok(Self::Cp850),
+            _ => return_errno_with_message!(Errno::EINVAL, "the code page is not supported"),
+        }
+    }
+
+    /// Decodes a byte in the code page.
+    pub(super) fn decode(&self, byte: u8) -> char {
+        if byte.is_ascii() {
+            return byte as char;
+        }
+        self.upper_half()[(byte - 0x80) as usize]
+    }
+
+    /// Encodes a character in the code page, if the code page contains the character.
+    pub(super) fn encode(&self, ch: char) -> Option<u8> {
+        if ch.is_ascii() {
+            return Some(ch as u8);
+        }
+        self.upper_half()
+            .iter()
+            .position(|&upper_ch| upper_ch == ch)
+            .map(|index| index as u8 + 0x80)
+    }
+
+    fn upper_half(&self) -> &'static [char; 128] {
+        match self {
+            Self::Cp437 => &CP437_UPPER_HALF,
+            Self::Cp850 => &CP850_UPPER_HALF,
+        }
+    }
+}
+
+#[rustfmt::skip]
+static CP437_UPPER_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static CP850_UPPER_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
@@ -0,0 +1,656 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use aster_rights::Full;
+use ostd::mm::VmIo;
+
+use super::{
+    codepage::Codepage,
+    fat::{ClusterId, FatType},
+    utils::{short_name_checksum, DosTimestamp},
+};
+use crate::{fs::utils::InodeType, prelude::*, vm::vmo::Vmo};
+
+/// The size of a directory entry.
+pub(super) const DENTRY_SIZE: usize = 32;
+/// The maximum length of a long name in UTF-16 code units.
+pub(super) const MAX_NAME_LEN: usize = 255;
+/// The maximum size of a directory, which can hold at most 65536 entries.
+pub(super) const MAX_DIR_SIZE: usize = 65536 * DENTRY_SIZE;
+
+/// The first byte of a deleted entry.
+pub(super) const DELETED_MARKER: u8 = 0xE5;
+/// The first byte of the entry that follows the last entry in the directory.
+const END_MARKER: u8 = 0x00;
+/// The first byte of a short name whose real first byte is 0xE5.
+const KANJI_MARKER: u8 = 0x05;
+
+/// The attribute of the long name entries.
+const LONG_NAME_ATTR: u8 = 0x0F;
+/// The flag in the order of the last long name entry, which is stored first.
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// The mask of the order of a long name entry.
+const LONG_ENTRY_ORDER_MASK: u8 = 0x1F;
+/// The number of UTF-16 code units in a long name entry.
+const CHARS_PER_LONG_ENTRY: usize = 13;
+
+/// The flag in the case flags if the base of the short name is displayed in lower case.
+const CASE_LOWER_BASE: u8 = 0x08;
+/// The flag in the case flags if the extension of the short name is displayed in lower case.
+const CASE_LOWER_EXT: u8 = 0x10;
+
+bitflags! {
+    /// The attributes of a directory entry.
+    pub(super) struct FatAttr: u8 {
+        const READ_ONLY = 0x01;
+        const HIDDEN = 0x02;
+        const SYSTEM = 0x04;
+        const VOLUME_ID = 0x08;
+        const DIRECTORY = 0x10;
+        const ARCHIVE = 0x20;
+    }
+}
+
+/// A short name entry, which describes a file or a directory.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Pod)]
+pub(super) struct RawShortDentry {
+    pub(super) name: [u8; 11],
+    pub(super) attr: u8,
+    /// The case flags used by Windows NT, which is reserved by the specification.
+    pub(super) case_flags: u8,
+    pub(super) create_centis: u8,
+    pub(super) create_time: u16,
+    pub(super) create_date: u16,
+    pub(super) access_date: u16,
+    pub(super) cluster_high: u16,
+    pub(super) modify_time: u16,
+    pub(super) modify_date: u16,
+    pub(super) cluster_low: u16,
+    pub(super) size: u32,
+}
+
+impl RawShortDentry {
+    /// Returns the first cluster, which is 0 if no cluster is allocated.
+    pub(super) fn start_cluster(&self, fat_type: FatType) -> ClusterId {
+        // The high half is used by other systems (e.g., OS/2) on FAT12/16 volumes.
+        if fat_type == FatType::Fat32 {
+            ((self.cluster_high as u32) << 16) | self.cluster_low as u32
+        } else {
+            self.cluster_low as u32
+        }
+    }
+
+    pub(super) fn set_start_cluster(&mut self, cluster: ClusterId) {
+        self.cluster_high = (cluster >> 16) as u16;
+        self.cluster_low = cluster as u16;
+    }
+
+    pub(super) fn attr(&self) -> FatAttr {
+        FatAttr::from_bits_truncate(self.attr)
+    }
+
+    pub(super) fn create_time(&self) -> DosTimestamp {
+        DosTimestamp {
+            date: self.create_date,
+            time: self.create_time,
+            centis: self.create_centis,
+        }
+    }
+
+    pub(super) fn set_create_time(&mut self, timestamp: DosTimestamp) {
+        self.create_date = timestamp.date;
+        self.create_time = timestamp.time;
+        self.create_centis = timestamp.centis;
+    }
+
+    pub(super) fn modify_time(&self) -> DosTimestamp {
+        DosTimestamp {
+            date: self.modify_date,
+            time: self.modify_time,
+            centis: 0,
+        }
+    }
+
+    pub(super) fn set_modify_time(&mut self, timestamp: DosTimestamp) {
+        self.modify_date = timestamp.date;
+        self.modify_time = timestamp.time;
+    }
+
+    /// Returns the access time, of which only the date is recorded.
+    pub(super) fn access_time(&self) -> DosTimestamp {
+        DosTimestamp {
+            date: self.access_date,
+            time: 0,
+            centis: 0,
+        }
+    }
+
+    pub(super) fn set_access_time(&mut self, timestamp: DosTimestamp) {
+        self.access_date = timestamp.date;
+    }
+}
+
+/// A long name entry, which stores a part of the long name of the following short name entry.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawLongDentry {
+    order: u8,
+    name1: [u8; 10],
+    attr: u8,
+    type_: u8,
+    checksum: u8,
+    name2: [u8; 12],
+    cluster_low: u16,
+    name3: [u8; 4],
+}
+
+impl RawLongDentry {
+    fn new(order: u8, checksum: u8, chars: &[u16; CHARS_PER_LONG_ENTRY]) -> Self {
+        let mut bytes = [0u8; 2 * CHARS_PER_LONG_ENTRY];
+        for (dst, ch) in bytes.chunks_exact_mut(2).zip(chars.iter()) {
+            dst.copy_from_slice(&ch.to_le_bytes());
+        }
+
+        Self {
+            order,
+            name1: bytes[0..10].try_into().unwrap(),
+            attr: LONG_NAME_ATTR,
+            type_: 0,
+            checksum,
+            name2: bytes[10..22].try_into().unwrap(),
+            cluster_low: 0,
+            name3: bytes[22..26].try_into().unwrap(),
+        }
+    }
+
+    fn chars(&self) -> [u16; CHARS_PER_LONG_ENTRY] {
+        let mut bytes = [0u8; 2 * CHARS_PER_LONG_ENTRY];
+        bytes[0..10].copy_from_slice(&self.name1);
+        bytes[10..22].copy_from_slice(&self.name2);
+        bytes[22..26].copy_from_slice(&self.name3);
+        core::array::from_fn(|index| u16::from_le_bytes([bytes[2 * index], bytes[2 * index + 1]]))
+    }
+}
+
+/// How the short names are displayed and created, which is specified by the `shortname`
+/// mount option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) enum ShortNameMode {
+    /// Displays the short names in lower case, and creates long names unless all upper case.
+    Lower,
+    /// Displays the short names as stored, and creates long names unless all upper case.
+    Win95,
+    /// Displays the short names with the case flags, and creates long names unless each part
+    /// is all upper case or all lower case.
+    WinNt,
+    /// Displays the short names with the case flags, and creates long names unless all upper
+    /// case.
+    #[default]
+    Mixed,
+}
+
+impl ShortNameMode {
+    pub(super) fn from_option(value: &str) -> Result<Self> {
+        match value {
+            "lower" => Ok(Self::Lower),
+            "win95" => Ok(Self::Win95),
+            "winnt" => Ok(Self::WinNt),
+            "mixed" => Ok(Self::Mixed),
+            _ => return_errno_with_message!(Errno::EINVAL, "the shortname option is invalid"),
+        }
+    }
+}
+
+/// A short name in the 8.3 format, whose base and extension are padded with spaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct ShortName([u8; 11]);
+
+impl ShortName {
+    /// The short name of the entry that refers to the directory itself.
+    pub(super) const DOT: Self = Self(*b".          ");
+    /// The short name of the entry that refers to the parent directory.
+    pub(super) const DOTDOT: Self = Self(*b"..         ");
+
+    pub(super) fn as_bytes(&self) -> &[u8; 11] {
+        &self.0
+    }
+
+    /// Converts the short name to a string to display.
+    fn to_display_string(&self, case_flags: u8, codepage: Codepage, mode: ShortNameMode) -> String {
+        let (lower_base, lower_ext) = match mode {
+            ShortNameMode::Lower => (true, true),
+            ShortNameMode::Win95 => (false, false),
+            ShortNameMode::WinNt | ShortNameMode::Mixed => (
+                case_flags & CASE_LOWER_BASE != 0,
+                case_flags & CASE_LOWER_EXT != 0,
+            ),
+        };
+
+        let decode = |bytes: &[u8], lower: bool| {
+            let mut part = String::new();
+            for (index, &byte) in bytes.iter().enumerate() {
+                let byte = if index == 0 && byte == KANJI_MARKER {
+                    DELETED_MARKER
+                } else {
+                    byte
+                };
+                let ch = codepage.decode(byte);
+                if lower {
+                    part.extend(ch.to_lowercase());
+                } else {
+                    part.push(ch);
+                }
+            }
+            part.truncate(part.trim_end_matches(' ').len());
+            part
+        };
+
+        let mut name = decode(&self.0[..8], lower_base);
+        let ext = decode(&self.0[8..], lower_ext);
+        if !ext.is_empty() {
+            name.push('.');
+            name.push_str(&ext);
+        }
+        name
+    }
+
+    /// Makes the short name that represents `name` exactly, with the case flags.
+    ///
+    /// If there is no such short name, a long name is required.
+    pub(super) fn from_exact_name(
+        name: &str,
+        codepage: Codepage,
+        mode: ShortNameMode,
+    ) -> Option<(Self, u8)> {
+        if name.starts_with('.') {
+            return None;
+        }
+        let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+        if base.contains('.') {
+            return None;
+        }
+
+        let mut short_name = [b' '; 11];
+        let mut case_flags = 0;
+        for (part, range, lower_flag, max_len) in [
+            (base, 0..8, CASE_LOWER_BASE, 8),
+            (ext, 8..11, CASE_LOWER_EXT, 3),
+        ] {
+            let mut has_upper = false;
+            let mut has_lower = false;
+            let mut len = 0;
+            for ch in part.chars() {
+                if len == max_len {
+                    return None;
+                }
+                let upper_ch = to_single_uppercase(ch)?;
+                let byte = codepage.encode(upper_ch)?;
+                if !is_valid_short_char(byte) {
+                    return None;
+                }
+                has_upper |= ch.is_uppercase();
+                has_lower |= ch.is_lowercase();
+                short_name[range.start + len] = byte;
+                len += 1;
+            }
+            if range.start == 0 && len == 0 {
+                return None;
+            }
+
+            match mode {
+                _ if !has_lower => (),
+                ShortNameMode::WinNt if !has_upper => case_flags |= lower_flag,
+                _ => return None,
+            }
+        }
+
+        if short_name[0] == DELETED_MARKER {
+            short_name[0] = KANJI_MARKER;
+        }
+        Some((Self(short_name), case_flags))
+    }
+
+    /// Generates a short name with a numeric tail (e.g., `LONGFI~1.TXT`) for a long name.
+    ///
+    /// The generated short name is not accepted by `exists`.
+    pub(super) fn generate(
+        name: &str,
+        codepage: Codepage,
+        exists: impl Fn(&Self) -> bool,
+    ) -> Result<Self> {
+        let convert = |part: &str, max_len: usize| -> Vec<u8> {
+            part.chars()
+                .filter(|&ch| ch != ' ' && ch != '.')
+                .map(|ch| {
+                    to_single_uppercase(ch)
+                        .and_then(|ch| codepage.encode(ch))
+                        .filter(|&byte| is_valid_short_char(byte))
+                        .unwrap_or(b'_')
+                })
+                .take(max_len)
+                .collect()
+        };
+
+        // The leading dots are not a part of the extension.
+        let trimmed = name.trim_start_matches('.');
+        let (base, ext) = trimmed.rsplit_once('.').unwrap_or((trimmed, ""));
+        let base = convert(base, 8);
+        let ext = convert(ext, 3);
+
+        let mut short_name = [b' '; 11];
+        short_name[8..8 + ext.len()].copy_from_slice(&ext);
+        for tail in 1..1_000_000u32 {
+            let tail = format!("~{}", tail);
+            let base_len = base.len().min(8 - tail.len());
+            short_name[..8].fill(b' ');
+            short_name[..base_len].copy_from_slice(&base[..base_len]);
+            short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+            if short_name[0] == DELETED_MARKER {
+                short_name[0] = KANJI_MARKER;
+            }
+
+            let short_name = Self(short_name);
+            if !exists(&short_name) {
+                return Ok(short_name);
+            }
+        }
+        return_errno_with_message!(Errno::EEXIST, "the short names are exhausted")
+    }
+
+    /// Returns the long name entries of `name` for this short name, in the on-disk order.
+    pub(super) fn long_entries(&self, name: &str) -> Vec<[u8; DENTRY_SIZE]> {
+        let checksum = short_name_checksum(&self.0);
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        let num_entries = units.len().div_ceil(CHARS_PER_LONG_ENTRY);
+        // The name is terminated by a NUL if there is room, and then padded with 0xFFFF.
+        if units.len() % CHARS_PER_LONG_ENTRY != 0 {
+            units.push(0);
+        }
+        units.resize(num_entries * CHARS_PER_LONG_ENTRY, 0xFFFF);
+
+        (0..num_entries)
+            .rev()
+            .map(|index| {
+                let mut order = index as u8 + 1;
+                if index == num_entries - 1 {
+                    order |= LAST_LONG_ENTRY;
+                }
+                let chars = units[index * CHARS_PER_LONG_ENTRY..(index + 1) * CHARS_PER_LONG_ENTRY]
+                    .try_into()
+                    .unwrap();
+                let mut entry = [0u8; DENTRY_SIZE];
+                entry.copy_from_slice(RawLongDentry::new(order, checksum, chars).as_bytes());
+                entry
+            })
+            .collect()
+    }
+}
+
+/// Converts a character to upper case, if the upper case is a single character.
+fn to_single_uppercase(ch: char) -> Option<char> {
+    let mut upper = ch.to_uppercase();
+    let upper_ch = upper.next()?;
+    upper.next().is_none().then_some(upper_ch)
+}
+
+/// Returns whether an encoded byte is allowed in the short names.
+fn is_valid_short_char(byte: u8) -> bool {
+    byte >= 0x80
+        || byte.is_ascii_uppercase()
+        || byte.is_ascii_digit()
+        || b"!#$%&'()-@^_`{}~".contains(&byte)
+}
+
+/// Checks a long name and returns the name to store, whose trailing dots are removed.
+pub(super) fn check_long_name(name: &str) -> Result<&str> {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "the name is empty");
+    }
+    if name.encode_utf16().count() > MAX_NAME_LEN {
+        return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+    }
+    if name
+        .chars()
+        .any(|ch| (ch as u32) < 0x20 || "\"*/:<>?\\|".contains(ch))
+    {
+        return_errno_with_message!(Errno::EINVAL, "the name contains invalid characters");
+    }
+    Ok(name)
+}
+
+/// Returns whether two names are the same, which are compared case-insensitively.
+pub(super) fn is_same_name(name: &str, other: &str) -> bool {
+    name.chars()
+        .flat_map(char::to_uppercase)
+        .eq(other.chars().flat_map(char::to_uppercase))
+}
+
+/// A directory entry, which consists of a short name entry and the long name entries before it.
+#[derive(Clone, Debug)]
+pub(super) struct DirEntry {
+    /// The long name, or the displayed short name if there is no valid long name.
+    pub(super) name: String,
+    pub(super) short_name: ShortName,
+    pub(super) dentry: RawShortDentry,
+    /// The offset of the first long name entry, or the short name entry if there is none.
+    pub(super) start_offset: usize,
+    /// The offset of the short name entry.
+    pub(super) offset: usize,
+}
+
+impl DirEntry {
+    /// Returns the range of all the entries.
+    pub(super) fn slots(&self) -> Range<usize> {
+        self.start_offset..self.offset + DENTRY_SIZE
+    }
+
+    /// Returns whether the entry is named `name`, which may be either its long name or its short
+    /// name.
+    pub(super) fn matches(&self, name: &str, codepage: Codepage) -> bool {
+        is_same_name(&self.name, name)
+            || is_same_name(
+                &self
+                    .short_name
+                    .to_display_string(0, codepage, ShortNameMode::Win95),
+                name,
+            )
+    }
+
+    /// Returns whether the entry is `.` or `..`.
+    pub(super) fn is_dot_or_dotdot(&self) -> bool {
+        self.short_name == ShortName::DOT || self.short_name == ShortName::DOTDOT
+    }
+
+    pub(super) fn type_(&self) -> InodeType {
+        if self.dentry.attr().contains(FatAttr::DIRECTORY) {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+}
+
+/// A reader of the raw entries in a directory, which reads the directory page by page.
+pub(super) struct DentryReader<'a> {
+    pages: &'a Vmo<Full>,
+    size: usize,
+    buf: Vec<u8>,
+    buf_offset: usize,
+}
+
+impl<'a> DentryReader<'a> {
+    /// Creates a reader of the directory whose contents of `size` bytes are in `pages`.
+    pub(super) fn new(pages: &'a Vmo<Full>, size: usize) -> Self {
+        Self {
+            pages,
+            size,
+            buf: Vec::new(),
+            buf_offset: 0,
+        }
+    }
+
+    /// Reads the raw entry at `offset`, or returns `None` if the offset is beyond the directory.
+    pub(super) fn read(&mut self, offset: usize) -> Result<Option<[u8; DENTRY_SIZE]>> {
+        if offset + DENTRY_SIZE > self.size {
+            return Ok(None);
+        }
+        if !(self.buf_offset..self.buf_offset + self.buf.len()).contains(&offset) {
+            self.buf_offset = offset - offset % PAGE_SIZE;
+            self.buf
+                .resize(PAGE_SIZE.min(self.size - self.buf_offset), 0);
+            self.pages.read_bytes(self.buf_offset, &mut self.buf)?;
+        }
+
+        let start = offset - self.buf_offset;
+        Ok(Some(
+            self.buf[start..start + DENTRY_SIZE].try_into().unwrap(),
+        ))
+    }
+
+    /// Finds `count` consecutive free entries and returns the offset of the first one.
+    ///
+    /// If there are not enough free entries, the returned entries extend beyond the end of the
+    /// directory, so the directory must be extended before they are written.
+    pub(super) fn find_free(&mut self, count: usize) -> Result<usize> {
+        let mut free_start = 0;
+        let mut num_free = 0;
+        let mut offset = 0;
+        while let Some(raw) = self.read(offset)? {
+            if raw[0] == END_MARKER || raw[0] == DELETED_MARKER {
+                if num_free == 0 {
+                    free_start = offset;
+                }
+                num_free += 1;
+                if num_free == count {
+                    return Ok(free_start);
+                }
+            } else {
+                num_free = 0;
+            }
+            offset += DENTRY_SIZE;
+        }
+        Ok(if num_free > 0 { free_start } else { self.size })
+    }
+
+    /// Returns an iterator over the entries starting from `offset`.
+    pub(super) fn iter(
+        self,
+        offset: usize,
+        codepage: Codepage,
+        mode: ShortNameMode,
+    ) -> DirEntryIter<'a> {
+        DirEntryIter {
+            reader: self,
+            offset,
+            codepage,
+            mode,
+        }
+    }
+}
+
+/// An iterator over the entries in a directory.
+///
+/// The volume label and the orphaned long name entries are skipped.
+pub(super) struct DirEntryIter<'a> {
+    reader: DentryReader<'a>,
+    offset: usize,
+    codepage: Codepage,
+    mode: ShortNameMode,
+}
+
+impl DirEntryIter<'_> {
+    fn next_entry(&mut self) -> Result<Option<DirEntry>> {
+        // The long name that is being collected, with the expected order of the next entry.
+        let mut long_name: Option<(Vec<u16>, u8, u8)> = None;
+        let mut start_offset = self.offset;
+
+        while let Some(raw) = self.reader.read(self.offset)? {
+            let offset = self.offset;
+            if raw[0] == END_MARKER {
+                return Ok(None);
+            }
+            self.offset += DENTRY_SIZE;
+            if raw[0] == DELETED_MARKER {
+                long_name = None;
+                continue;
+            }
+
+            if raw[11] == LONG_NAME_ATTR {
+                let long_entry = RawLongDentry::from_bytes(&raw);
+                let order = long_entry.order & LONG_ENTRY_ORDER_MASK;
+                if long_entry.order & LAST_LONG_ENTRY != 0 {
+                    if order == 0 {
+                        long_name = None;
+                        continue;
+                    }
+                    let units = vec![0u16; order as usize * CHARS_PER_LONG_ENTRY];
+                    long_name = Some((units, order, long_entry.checksum));
+                    start_offset = offset;
+                }
+                // An entry out of order invalidates the whole long name.
+                match long_name.as_mut() {
+                    Some((units, expected, checksum))
+                        if *expected == order && *checksum == long_entry.checksum =>
+                    {
+                        let index = (order - 1) as usize * CHARS_PER_LONG_ENTRY;
+                        units[index..index + CHARS_PER_LONG_ENTRY]
+                            .copy_from_slice(&long_entry.chars());
+                        *expected -= 1;
+                    }
+                    _ => long_name = None,
+                }
+                continue;
+            }
+
+            let dentry = RawShortDentry::from_bytes(&raw);
+            if dentry.attr().contains(FatAttr::VOLUME_ID) {
+                long_name = None;
+                continue;
+            }
+            let short_name = ShortName(dentry.name);
+
+            let long_name = long_name
+                .take()
+                .filter(|(_, expected, checksum)| {
+                    *expected == 0 && *checksum == short_name_checksum(&dentry.name)
+                })
+                .map(|(units, _, _)| {
+                    let len = units
+                        .iter()
+                        .position(|&unit| unit == 0)
+                        .unwrap_or(units.len());
+                    char::decode_utf16(units[..len].iter().copied())
+                        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect::<String>()
+                });
+            let (name, start_offset) = match long_name {
+                Some(name) => (name, start_offset),
+                None => (
+                    short_name.to_display_string(dentry.case_flags, self.codepage, self.mode),
+                    offset,
+                ),
+            };
+
+            return Ok(Some(DirEntry {
+                name,
+                short_name,
+                dentry,
+                start_offset,
+                offset,
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+impl Iterator for DirEntryIter<'_> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
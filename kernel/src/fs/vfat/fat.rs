@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::fs::VfatFS;
+use crate::prelude::*;
+
+/// The index of a cluster.
+pub(super) type ClusterId = u32;
+
+/// Cluster 0 and 1 are reserved, so the first data cluster is 2.
+pub(super) const FIRST_DATA_CLUSTER: ClusterId = 2;
+
+/// The type of a FAT volume, which determines the width of the FAT entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// Returns the size in bytes of the FAT entries of `num_entries` clusters.
+    pub(super) fn entries_size(&self, num_entries: u32) -> usize {
+        let num_entries = num_entries as usize;
+        match self {
+            Self::Fat12 => num_entries + num_entries.div_ceil(2),
+            Self::Fat16 => num_entries * 2,
+            Self::Fat32 => num_entries * 4,
+        }
+    }
+
+    /// Returns the mask of the valid bits in the FAT entries.
+    fn mask(&self) -> u32 {
+        match self {
+            Self::Fat12 => 0x0FFF,
+            Self::Fat16 => 0xFFFF,
+            Self::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    fn decode(&self, raw: u32) -> FatEntry {
+        let value = raw & self.mask();
+        // The values from the bad cluster marker to the mask are reserved.
+        let bad_cluster = self.mask() - 8;
+        match value {
+            0 => FatEntry::Free,
+            value if value == bad_cluster => FatEntry::Bad,
+            value if value > bad_cluster => FatEntry::EndOfChain,
+            value => FatEntry::Next(value),
+        }
+    }
+
+    fn encode(&self, entry: FatEntry) -> u32 {
+        match entry {
+            FatEntry::Free => 0,
+            FatEntry::Next(cluster) => cluster,
+            FatEntry::Bad => self.mask() - 8,
+            FatEntry::EndOfChain => self.mask(),
+        }
+    }
+}
+
+/// An entry in the FAT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatEntry {
+    /// The cluster is free.
+    Free,
+    /// The cluster is followed by another cluster.
+    Next(ClusterId),
+    /// The cluster is bad and must not be used.
+    Bad,
+    /// The cluster is the last one of a chain.
+    EndOfChain,
+}
+
+/// The state of the cluster allocator.
+#[derive(Debug)]
+pub(super) struct ClusterAllocator {
+    /// The number of the free clusters.
+    pub(super) num_free: u32,
+    /// The cluster where to search for the next free cluster.
+    pub(super) next_free: ClusterId,
+}
+
+impl VfatFS {
+    /// Reads the FAT entry of the `cluster`.
+    pub(super) fn read_fat(&self, cluster: ClusterId) -> Result<FatEntry> {
+        let sb = self.super_block();
+        if !sb.is_valid_cluster(cluster) {
+            return_errno_with_message!(Errno::EIO, "invalid access to the FAT");
+        }
+
+        let fat_start = sb.fat_start + sb.active_fat.unwrap_or(0) * sb.fat_size;
+        let raw = match sb.fat_type {
+            FatType::Fat12 => {
+                let offset = fat_start + cluster as usize + cluster as usize / 2;
+                let value = self.read_meta_val::<u16>(offset)?;
+                if cluster % 2 == 0 {
+                    (value & 0x0FFF) as u32
+                } else {
+                    (value >> 4) as u32
+                }
+            }
+            FatType::Fat16 => self.read_meta_val::<u16>(fat_start + cluster as usize * 2)? as u32,
+            FatType::Fat32 => self.read_meta_val::<u32>(fat_start + cluster as usize * 4)?,
+        };
+        Ok(sb.fat_type.decode(raw))
+    }
+
+    /// Writes the FAT entry of the `cluster` to all the FATs in use.
+    pub(super) fn write_fat(
+        &self,
+        cluster: ClusterId,
+        entry: FatEntry,
+        _fs_guard: &MutexGuard<()>,
+    ) -> Result<()> {
+        let sb = self.super_block();
+        debug_assert!(sb.is_valid_cluster(cluster));
+
+        let value = sb.fat_type.encode(entry);
+        let fat_indexes = match sb.active_fat {
+            Some(index) => index..index + 1,
+            None => 0..sb.num_fats,
+        };
+        for fat_index in fat_indexes {
+            let fat_start = sb.fat_start + fat_index * sb.fat_size;
+            match sb.fat_type {
+                FatType::Fat12 => {
+                    // Two entries share the middle byte, so the other half must be preserved.
+                    let offset = fat_start + cluster as usize + cluster as usize / 2;
+                    let old_value = self.read_meta_val::<u16>(offset)?;
+                    let new_value = if cluster % 2 == 0 {
+                        (old_value & 0xF000) | value as u16
+                    } else {
+                        (old_value & 0x000F) | ((value as u16) << 4)
+                    };
+                    self.write_meta_val(offset, &new_value)?;
+                }
+                FatType::Fat16 => {
+                    self.write_meta_val(fat_start + cluster as usize * 2, &(value as u16))?;
+                }
+                FatType::Fat32 => {
+                    // The upper 4 bits are reserved and must be preserved.
+                    let offset = fat_start + cluster as usize * 4;
+                    let old_value = self.read_meta_val::<u32>(offset)?;
+                    self.write_meta_val(offset, &((old_value & 0xF000_0000) | value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the cluster chain that starts from the `start` cluster.
+    ///
+    /// A chain that starts from cluster 0 is empty.
+    pub(super) fn read_chain(&self, start: ClusterId) -> Result<Vec<ClusterId>> {
+        let mut clusters = Vec::new();
+        if start == 0 {
+            return Ok(clusters);
+        }
+
+        let mut cluster = start;
+        loop {
+            // A chain longer than the volume must contain a loop.
+            if clusters.len() >= self.super_block().num_clusters as usize {
+                return_errno_with_message!(Errno::EIO, "the cluster chain contains a loop");
+            }
+            clusters.push(cluster);
+            match self.read_fat(cluster)? {
+                FatEntry::Next(next) => cluster = next,
+                FatEntry::EndOfChain => break,
+                FatEntry::Free | FatEntry::Bad => {
+                    return_errno_with_message!(Errno::EIO, "the cluster chain is broken");
+                }
+            }
+        }
+        Ok(clusters)
+    }
+
+    /// Allocates `count` clusters and appends them to the chain that ends with `last`.
+    ///
+    /// A new chain is allocated if `last` is `None`. The newly allocated clusters are returned.
+    pub(super) fn alloc_clusters(
+        &self,
+        last: Option<ClusterId>,
+        count: usize,
+        fs_guard: &MutexGuard<()>,
+    ) -> Result<Vec<ClusterId>> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        if (self.allocator().num_free as usize) < count {
+            self.reclaim_orphans(fs_guard)?;
+            if (self.allocator().num_free as usize) < count {
+                return_errno_with_message!(Errno::ENOSPC, "no free clusters");
+            }
+        }
+
+        let sb = self.super_block();
+        let end = sb.num_clusters + FIRST_DATA_CLUSTER;
+        let mut clusters = Vec::with_capacity(count);
+        let mut cluster = self.allocator().next_free;
+        for _ in 0..sb.num_clusters {
+            if !sb.is_valid_cluster(cluster) {
+                cluster = FIRST_DATA_CLUSTER;
+            }
+            if self.read_fat(cluster)? == FatEntry::Free {
+                clusters.push(cluster);
+                if clusters.len() == count {
+                    break;
+                }
+            }
+            cluster += 1;
+        }
+        if clusters.len() < count {
+            // The free count is wrong, so fix it to avoid the futile search next time.
+            self.allocator().num_free = clusters.len() as u32;
+            return_errno_with_message!(Errno::ENOSPC, "no free clusters");
+        }
+
+        // The new clusters are linked before they are appended, so the existing chain is always
+        // valid.
+        for (index, &cluster) in clusters.iter().enumerate() {
+            let entry = match clusters.get(index + 1) {
+                Some(&next) => FatEntry::Next(next),
+                None => FatEntry::EndOfChain,
+            };
+            self.write_fat(cluster, entry, fs_guard)?;
+        }
+        if let Some(last) = last {
+            self.write_fat(last, FatEntry::Next(clusters[0]), fs_guard)?;
+        }
+
+        let mut allocator = self.allocator();
+        allocator.num_free -= count as u32;
+        let next_free = clusters[count - 1] + 1;
+        allocator.next_free = if next_free < end {
+            next_free
+        } else {
+            FIRST_DATA_CLUSTER
+        };
+        Ok(clusters)
+    }
+
+    /// Frees the `clusters`, which must be the tail of a chain or a whole chain.
+    pub(super) fn free_clusters(
+        &self,
+        clusters: &[ClusterId],
+        fs_guard: &MutexGuard<()>,
+    ) -> Result<()> {
+        for &cluster in clusters {
+            self.write_fat(cluster, FatEntry::Free, fs_guard)?;
+        }
+        self.allocator().num_free += clusters.len() as u32;
+        Ok(())
+    }
+
+    /// Counts the free clusters by scanning the FAT.
+    pub(super) fn count_free_clusters(&self) -> Result<u32> {
+        let sb = self.super_block();
+        let mut num_free = 0;
+        for cluster in FIRST_DATA_CLUSTER..sb.num_clusters + FIRST_DATA_CLUSTER {
+            if self.read_fat(cluster)? == FatEntry::Free {
+                num_free += 1;
+            }
+        }
+        Ok(num_free)
+    }
+}
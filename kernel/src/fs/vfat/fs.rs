@@ -0,0 +1,459 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+use aster_block::{
+    bio::{Bio, BioDirection, BioSegment, BioType, BioWaiter},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use hashbrown::HashMap;
+use ostd::{
+    mm::{Segment, UntypedMem, VmIo},
+    sync::PreemptDisabled,
+};
+use spin::Once;
+
+use super::{
+    codepage::Codepage,
+    dentry::{ShortNameMode, MAX_NAME_LEN},
+    fat::{ClusterAllocator, FatType, FIRST_DATA_CLUSTER},
+    inode::VfatInode,
+    super_block::{RawBootSector, RawFsInfo, SuperBlock, VFAT_MAGIC},
+};
+use crate::{
+    fs::utils::{
+        CachePage, FileSystem, FsFlags, Inode, InodeMode, PageCache, PageCacheBackend,
+        SuperBlock as VfsSuperBlock,
+    },
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The FAT file system with long file names (VFAT).
+#[derive(Debug)]
+pub struct VfatFS {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: SuperBlock,
+    options: VfatMountOptions,
+    /// The page cache of the reserved sectors and the FATs.
+    ///
+    /// The directories and the files are cached by their own inodes, so that the data clusters
+    /// are never written through this cache.
+    meta_cache: PageCache,
+    allocator: SpinLock<ClusterAllocator>,
+    root: Once<Arc<VfatInode>>,
+    /// The opened inodes, indexed by the device offsets of their short name entries.
+    inodes: RwMutex<HashMap<usize, Arc<VfatInode>>>,
+    /// The deleted inodes that are still in use, whose clusters are freed after they are released.
+    orphans: Mutex<Vec<Arc<VfatInode>>>,
+    /// A global lock, which must be held when the FATs or the directories are modified.
+    mutex: Mutex<()>,
+}
+
+impl VfatFS {
+    /// Opens a FAT12/16/32 volume on the `block_device`.
+    pub fn open(
+        block_device: Arc<dyn BlockDevice>,
+        options: VfatMountOptions,
+    ) -> Result<Arc<Self>> {
+        let super_block = Self::read_super_block(block_device.as_ref())?;
+        if super_block.data_start + super_block.data_size()
+            > block_device.metadata().nr_sectors * SECTOR_SIZE
+        {
+            return_errno_with_message!(Errno::EINVAL, "the volume is larger than the device");
+        }
+
+        let fs = Arc::new_cyclic(|weak_self| Self {
+            block_device,
+            super_block,
+            options,
+            meta_cache: PageCache::with_capacity(super_block.fat_end(), weak_self.clone() as _)
+                .unwrap(),
+            allocator: SpinLock::new(ClusterAllocator {
+                num_free: 0,
+                next_free: FIRST_DATA_CLUSTER,
+            }),
+            root: Once::new(),
+            inodes: RwMutex::new(HashMap::new()),
+            orphans: Mutex::new(Vec::new()),
+            mutex: Mutex::new(()),
+        });
+
+        fs.check_volume_state()?;
+        *fs.allocator() = fs.load_allocator()?;
+        let root = VfatInode::new_root(&fs)?;
+        fs.root.call_once(|| root);
+
+        Ok(fs)
+    }
+
+    fn read_super_block(block_device: &dyn BlockDevice) -> Result<SuperBlock> {
+        let mut boot_sector = [0u8; SECTOR_SIZE];
+        block_device.read_bytes(0, &mut boot_sector)?;
+        let raw_boot_sector = RawBootSector::from_bytes(&boot_sector[..size_of::<RawBootSector>()]);
+        SuperBlock::try_from(raw_boot_sector)
+    }
+
+    /// Warns if the volume was not unmounted cleanly, which is recorded in the entry of cluster 1.
+    fn check_volume_state(&self) -> Result<()> {
+        let sb = self.super_block();
+        let is_clean = match sb.fat_type {
+            FatType::Fat12 => true,
+            FatType::Fat16 => self.read_meta_val::<u16>(sb.fat_start + 2)? & 0x8000 != 0,
+            FatType::Fat32 => self.read_meta_val::<u32>(sb.fat_start + 4)? & 0x0800_0000 != 0,
+        };
+        if !is_clean {
+            warn!(
+                "The volume was not properly unmounted. Some data may be corrupt. Please run fsck."
+            );
+        }
+        Ok(())
+    }
+
+    fn load_allocator(&self) -> Result<ClusterAllocator> {
+        let sb = self.super_block();
+        let fs_info = self.read_fs_info()?;
+        let num_free = match fs_info.and_then(|fs_info| fs_info.free_count()) {
+            Some(num_free) if num_free <= sb.num_clusters => num_free,
+            _ => self.count_free_clusters()?,
+        };
+        let next_free = fs_info
+            .and_then(|fs_info| fs_info.next_free())
+            .filter(|&cluster| sb.is_valid_cluster(cluster))
+            .unwrap_or(FIRST_DATA_CLUSTER);
+
+        Ok(ClusterAllocator {
+            num_free,
+            next_free,
+        })
+    }
+
+    fn read_fs_info(&self) -> Result<Option<RawFsInfo>> {
+        match self.super_block.fs_info {
+            Some(offset) => Ok(Some(self.read_meta_val(offset)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the allocation hints back to the FSInfo sector.
+    fn sync_fs_info(&self) -> Result<()> {
+        let (Some(offset), Some(mut fs_info)) = (self.super_block.fs_info, self.read_fs_info()?)
+        else {
+            return Ok(());
+        };
+        let (num_free, next_free) = {
+            let allocator = self.allocator();
+            (allocator.num_free, allocator.next_free)
+        };
+        fs_info.set_hints(num_free, next_free);
+        self.write_meta_val(offset, &fs_info)
+    }
+
+    pub(super) fn super_block(&self) -> SuperBlock {
+        self.super_block
+    }
+
+    pub(super) fn options(&self) -> &VfatMountOptions {
+        &self.options
+    }
+
+    pub(super) fn allocator(&self) -> SpinLockGuard<ClusterAllocator, PreemptDisabled> {
+        self.allocator.lock()
+    }
+
+    pub(super) fn lock(&self) -> MutexGuard<()> {
+        self.mutex.lock()
+    }
+
+    pub(super) fn root(&self) -> &Arc<VfatInode> {
+        self.root.get().unwrap()
+    }
+
+    pub(super) fn read_meta_val<T: Pod>(&self, offset: usize) -> Result<T> {
+        Ok(self.meta_cache.pages().read_val(offset)?)
+    }
+
+    pub(super) fn write_meta_val<T: Pod>(&self, offset: usize, val: &T) -> Result<()> {
+        self.meta_cache.pages().write_val(offset, val)?;
+        Ok(())
+    }
+
+    pub(super) fn find_inode(&self, dentry_pos: usize) -> Option<Arc<VfatInode>> {
+        self.inodes.read().get(&dentry_pos).cloned()
+    }
+
+    pub(super) fn insert_inode(&self, dentry_pos: usize, inode: Arc<VfatInode>) {
+        self.inodes.write().insert(dentry_pos, inode);
+    }
+
+    pub(super) fn remove_inode(&self, dentry_pos: usize) -> Option<Arc<VfatInode>> {
+        self.inodes.write().remove(&dentry_pos)
+    }
+
+    /// Keeps a deleted inode until it is no longer used.
+    pub(super) fn add_orphan(
+        &self,
+        inode: Arc<VfatInode>,
+        fs_guard: &MutexGuard<()>,
+    ) -> Result<()> {
+        self.orphans.lock().push(inode);
+        self.reclaim_orphans(fs_guard)
+    }
+
+    /// Frees the clusters of the deleted inodes that are no longer used.
+    pub(super) fn reclaim_orphans(&self, fs_guard: &MutexGuard<()>) -> Result<()> {
+        let unused_orphans: Vec<Arc<VfatInode>> = {
+            let mut orphans = self.orphans.lock();
+            let (unused, used) = orphans
+                .drain(..)
+                .partition(|inode| Arc::strong_count(inode) == 1);
+            *orphans = used;
+            unused
+        };
+        for orphan in unused_orphans {
+            orphan.free_all_clusters(fs_guard)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a page of `frame` asynchronously from the device `runs`.
+    ///
+    /// The parts of the page that are not covered by the runs are filled with zeros.
+    pub(super) fn read_page_runs(
+        &self,
+        frame: &CachePage,
+        runs: &[DeviceRun],
+    ) -> Result<BioWaiter> {
+        if let [run] = runs {
+            if run.len == PAGE_SIZE {
+                let bio_segment = BioSegment::new_from_segment(
+                    Segment::from(frame.clone()).into(),
+                    BioDirection::FromDevice,
+                );
+                let bio = Bio::new(
+                    BioType::Read,
+                    Sid::from_offset(run.device_offset),
+                    vec![bio_segment],
+                    None,
+                );
+                return Ok(bio.submit(self.block_device.as_ref())?);
+            }
+        }
+
+        // The runs that are smaller than a page are read synchronously, since a bio segment
+        // cannot cover a part of a frame.
+        frame.writer().fill(0u8);
+        for run in runs {
+            let mut writer = frame.writer();
+            writer.skip(run.page_offset).limit(run.len);
+            self.block_device
+                .read(run.device_offset, &mut writer.to_fallible())?;
+        }
+        Ok(BioWaiter::new())
+    }
+
+    /// Writes a page of `frame` asynchronously to the device `runs`.
+    pub(super) fn write_page_runs(
+        &self,
+        frame: &CachePage,
+        runs: &[DeviceRun],
+    ) -> Result<BioWaiter> {
+        let mut bio_waiter = BioWaiter::new();
+        let mut buf = vec![0u8; PAGE_SIZE];
+        frame.reader().read(&mut VmWriter::from(buf.as_mut_slice()));
+        for run in runs {
+            let run_buf = &buf[run.page_offset..run.page_offset + run.len];
+            let waiter = self
+                .block_device
+                .write_bytes_async(run.device_offset, run_buf)?;
+            bio_waiter.concat(waiter);
+        }
+        Ok(bio_waiter)
+    }
+
+    /// Writes back the reserved sectors and the FATs.
+    pub(super) fn sync_meta(&self) -> Result<()> {
+        self.meta_cache.evict_range(0..self.super_block.fat_end())
+    }
+
+    fn sync_all_inodes(&self) -> Result<()> {
+        let inodes: Vec<Arc<VfatInode>> = self.inodes.read().values().cloned().collect();
+        for inode in inodes.iter().chain(core::iter::once(self.root())) {
+            inode.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A contiguous range of bytes on the device that backs a part of a page.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct DeviceRun {
+    /// The offset within the page.
+    pub(super) page_offset: usize,
+    /// The offset on the device.
+    pub(super) device_offset: usize,
+    pub(super) len: usize,
+}
+
+impl PageCacheBackend for VfatFS {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.read_page_runs(frame, &self.meta_runs(idx)?)
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.write_page_runs(frame, &self.meta_runs(idx)?)
+    }
+
+    fn npages(&self) -> usize {
+        self.super_block.fat_end().align_up(PAGE_SIZE) / PAGE_SIZE
+    }
+}
+
+impl VfatFS {
+    /// Returns the device run of a page in the metadata cache, which maps to the same offset.
+    fn meta_runs(&self, idx: usize) -> Result<Vec<DeviceRun>> {
+        let start = idx * PAGE_SIZE;
+        let end = (start + PAGE_SIZE).min(self.super_block.fat_end());
+        if start >= end {
+            return_errno_with_message!(Errno::EINVAL, "the page is beyond the FATs");
+        }
+        Ok(vec![DeviceRun {
+            page_offset: 0,
+            device_offset: start,
+            len: end - start,
+        }])
+    }
+}
+
+impl FileSystem for VfatFS {
+    fn sync(&self) -> Result<()> {
+        {
+            let fs_guard = self.lock();
+            self.reclaim_orphans(&fs_guard)?;
+            self.sync_fs_info()?;
+        }
+        self.sync_all_inodes()?;
+        self.sync_meta()?;
+
+        self.block_device.sync()?;
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root().clone()
+    }
+
+    fn sb(&self) -> VfsSuperBlock {
+        let mut sb = VfsSuperBlock::new(VFAT_MAGIC, self.super_block.cluster_size, MAX_NAME_LEN);
+        sb.blocks = self.super_block.num_clusters as usize;
+        sb.bfree = self.allocator().num_free as usize;
+        sb.bavail = sb.bfree;
+        sb.fsid = self.super_block.volume_id as u64;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+/// The mount options of the FAT file system.
+#[derive(Clone, Debug)]
+pub struct VfatMountOptions {
+    /// The owner of all the files.
+    pub(super) uid: Uid,
+    /// The group of all the files.
+    pub(super) gid: Gid,
+    /// The permission bits that are removed from the files.
+    pub(super) fmask: InodeMode,
+    /// The permission bits that are removed from the directories.
+    pub(super) dmask: InodeMode,
+    /// The code page of the short names.
+    pub(super) codepage: Codepage,
+    pub(super) shortname: ShortNameMode,
+    /// The offset of the local time in the timestamps from UTC, in minutes.
+    pub(super) time_offset: i32,
+    /// Whether only the files with the extensions of executables are executable.
+    pub(super) showexec: bool,
+    /// Whether the failures of changing the owner and the mode are ignored.
+    pub(super) quiet: bool,
+    /// Whether the data are written back to the device immediately.
+    pub(super) flush: bool,
+}
+
+impl Default for VfatMountOptions {
+    fn default() -> Self {
+        Self {
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            fmask: InodeMode::from_bits_truncate(0o022),
+            dmask: InodeMode::from_bits_truncate(0o022),
+            codepage: Codepage::default(),
+            shortname: ShortNameMode::default(),
+            time_offset: 0,
+            showexec: false,
+            quiet: false,
+            flush: false,
+        }
+    }
+}
+
+impl VfatMountOptions {
+    /// Parses the options from the mount data, e.g., `uid=1000,umask=022,codepage=437`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let parse_num = |radix| {
+                u32::from_str_radix(value, radix).map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the vfat mount option is invalid")
+                })
+            };
+            let parse_mask = || parse_num(8).map(|mask| InodeMode::from_bits_truncate(mask as u16));
+
+            match key {
+                "uid" => options.uid = Uid::new(parse_num(10)?),
+                "gid" => options.gid = Gid::new(parse_num(10)?),
+                "umask" => {
+                    options.fmask = parse_mask()?;
+                    options.dmask = options.fmask;
+                }
+                "fmask" => options.fmask = parse_mask()?,
+                "dmask" => options.dmask = parse_mask()?,
+                "codepage" => options.codepage = Codepage::from_number(value)?,
+                // The names are always UTF-8 strings in the VFS.
+                "iocharset" if value == "utf8" || value == "utf-8" => (),
+                "utf8" if matches!(value, "" | "1" | "yes" | "true") => (),
+                "iocharset" | "utf8" => {
+                    return_errno_with_message!(Errno::EINVAL, "only UTF-8 names are supported")
+                }
+                "shortname" => options.shortname = ShortNameMode::from_option(value)?,
+                "time_offset" => {
+                    let time_offset = value.parse::<i32>().map_err(|_| {
+                        Error::with_message(Errno::EINVAL, "the vfat mount option is invalid")
+                    })?;
+                    if time_offset.abs() > 24 * 60 {
+                        return_errno_with_message!(Errno::EINVAL, "the time offset is too large");
+                    }
+                    options.time_offset = time_offset;
+                }
+                "tz" if value == "UTC" => options.time_offset = 0,
+                "showexec" => options.showexec = true,
+                "quiet" => options.quiet = true,
+                "flush" => options.flush = true,
+                // The names are always checked in the relaxed way, and the errors are reported
+                // to the callers without other actions.
+                "check" | "errors" | "discard" => (),
+                _ => return_errno_with_message!(Errno::EINVAL, "the vfat mount option is unknown"),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Returns the offset of the local time from UTC in seconds.
+    pub(super) fn time_offset_secs(&self) -> i64 {
+        self.time_offset as i64 * 60
+    }
+}
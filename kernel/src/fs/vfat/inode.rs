@@ -0,0 +1,1167 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use align_ext::AlignExt;
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::mm::VmIo;
+
+use super::{
+    dentry::{
+        check_long_name, DentryReader, DirEntry, DirEntryIter, FatAttr, RawShortDentry, ShortName,
+        DELETED_MARKER, DENTRY_SIZE, MAX_DIR_SIZE,
+    },
+    fat::{ClusterId, FatEntry, FatType},
+    fs::{DeviceRun, VfatFS},
+    utils::{now, DosTimestamp},
+};
+use crate::{
+    fs::{
+        path::{is_dot, is_dot_or_dotdot},
+        utils::{
+            CachePage, DirentVisitor, ExtendedMetadata, FileSystem, Inode, InodeMode, InodeType,
+            Metadata, PageCache, PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The inode number of the root directory.
+const ROOT_INO: u64 = 1;
+/// The maximum size of a file, which is limited by the 32-bit size field.
+const MAX_FILE_SIZE: usize = u32::MAX as usize;
+/// The number of the `.` and `..` entries, which are emitted first by `readdir_at`.
+const NUM_SPECIAL_ENTRIES: usize = 2;
+
+/// The permission bits that allow writing.
+const WRITE_BITS: InodeMode = InodeMode::from_bits_truncate(0o222);
+/// The permission bits that allow executing.
+const EXEC_BITS: InodeMode = InodeMode::from_bits_truncate(0o111);
+
+/// An inode of the FAT file system, which is either a regular file or a directory.
+///
+/// FAT has no on-disk inodes. All the metadata of a file is stored in its short name entry, so
+/// the inode number is derived from the location of the entry.
+pub struct VfatInode {
+    ino: u64,
+    type_: InodeType,
+    /// The location of the short name entry, which is `None` for the root directory.
+    location: RwMutex<Option<DentryLocation>>,
+    meta: RwMutex<InodeMeta>,
+    /// The clusters of the inode, in order.
+    ///
+    /// The lock must not be held for writing when the page cache is accessed, because the page
+    /// cache reads the clusters when it loads and writes back pages.
+    clusters: RwMutex<Vec<ClusterId>>,
+    page_cache: PageCache,
+    fs: Weak<VfatFS>,
+    this: Weak<VfatInode>,
+}
+
+/// The location of a short name entry.
+#[derive(Clone)]
+struct DentryLocation {
+    parent: Arc<VfatInode>,
+    /// The offset of the entry in the parent directory.
+    offset: usize,
+}
+
+struct InodeMeta {
+    /// The short name entry, of which the size and the first cluster are not maintained.
+    dentry: RawShortDentry,
+    size: usize,
+    /// The change time, which is not recorded on disk.
+    ctime: Duration,
+    is_deleted: bool,
+}
+
+impl VfatInode {
+    pub(super) fn new_root(fs: &Arc<VfatFS>) -> Result<Arc<Self>> {
+        let sb = fs.super_block();
+        let mut dentry = RawShortDentry::default();
+        dentry.attr = FatAttr::DIRECTORY.bits();
+
+        // The root directory of FAT12/16 is a fixed region before the data clusters.
+        let (clusters, size) = if sb.fat_type == FatType::Fat32 {
+            let clusters = fs.read_chain(sb.root_cluster)?;
+            let size = clusters.len() * sb.cluster_size;
+            (clusters, size)
+        } else {
+            (Vec::new(), sb.root_dir_size)
+        };
+
+        Ok(Self::new(
+            fs,
+            ROOT_INO,
+            InodeType::Dir,
+            None,
+            dentry,
+            clusters,
+            size,
+        ))
+    }
+
+    fn new(
+        fs: &Arc<VfatFS>,
+        ino: u64,
+        type_: InodeType,
+        location: Option<DentryLocation>,
+        dentry: RawShortDentry,
+        clusters: Vec<ClusterId>,
+        size: usize,
+    ) -> Arc<Self> {
+        let ctime = dentry
+            .modify_time()
+            .as_duration(fs.options().time_offset_secs());
+        Arc::new_cyclic(|weak_self| Self {
+            ino,
+            type_,
+            location: RwMutex::new(location),
+            meta: RwMutex::new(InodeMeta {
+                dentry,
+                size,
+                ctime,
+                is_deleted: false,
+            }),
+            clusters: RwMutex::new(clusters),
+            page_cache: PageCache::with_capacity(size, weak_self.clone() as _).unwrap(),
+            fs: Arc::downgrade(fs),
+            this: weak_self.clone(),
+        })
+    }
+
+    fn fs(&self) -> Arc<VfatFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    /// Returns the parent directory. The parent of the root directory is itself.
+    fn parent(&self) -> Arc<Self> {
+        match self.location.read().as_ref() {
+            Some(location) => location.parent.clone(),
+            None => self.this(),
+        }
+    }
+
+    fn is_root(&self) -> bool {
+        self.ino == ROOT_INO
+    }
+
+    fn is_fixed_root(&self) -> bool {
+        self.is_root() && self.fs().super_block().fat_type != FatType::Fat32
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the allocated space.
+    fn capacity(&self) -> usize {
+        let sb = self.fs().super_block();
+        if self.is_fixed_root() {
+            return sb.root_dir_size;
+        }
+        self.clusters.read().len() * sb.cluster_size
+    }
+
+    /// Returns the device offset of the byte at `offset`, which must be within the capacity.
+    fn device_offset(&self, offset: usize) -> usize {
+        let sb = self.fs().super_block();
+        if self.is_fixed_root() {
+            return sb.root_dir_start + offset;
+        }
+        let clusters = self.clusters.read();
+        sb.cluster_offset(clusters[offset / sb.cluster_size]) + offset % sb.cluster_size
+    }
+
+    /// Returns the device runs that back the page at `idx`.
+    fn device_runs(&self, idx: usize) -> Result<Vec<DeviceRun>> {
+        let sb = self.fs().super_block();
+        let page_start = idx * PAGE_SIZE;
+
+        if self.is_fixed_root() {
+            let page_end = (page_start + PAGE_SIZE).min(sb.root_dir_size);
+            if page_start >= page_end {
+                return_errno_with_message!(Errno::EINVAL, "the page is beyond the root directory");
+            }
+            return Ok(vec![DeviceRun {
+                page_offset: 0,
+                device_offset: sb.root_dir_start + page_start,
+                len: page_end - page_start,
+            }]);
+        }
+
+        let clusters = self.clusters.read();
+        let page_end = (page_start + PAGE_SIZE).min(clusters.len() * sb.cluster_size);
+        if page_start >= page_end {
+            return_errno_with_message!(Errno::EINVAL, "the page is beyond the clusters");
+        }
+
+        let mut runs: Vec<DeviceRun> = Vec::new();
+        let mut offset = page_start;
+        while offset < page_end {
+            let offset_in_cluster = offset % sb.cluster_size;
+            let len = (sb.cluster_size - offset_in_cluster).min(page_end - offset);
+            let device_offset =
+                sb.cluster_offset(clusters[offset / sb.cluster_size]) + offset_in_cluster;
+            // The adjacent clusters are merged into one run.
+            match runs.last_mut() {
+                Some(run) if run.device_offset + run.len == device_offset => run.len += len,
+                _ => runs.push(DeviceRun {
+                    page_offset: offset - page_start,
+                    device_offset,
+                    len,
+                }),
+            }
+            offset += len;
+        }
+        Ok(runs)
+    }
+
+    /// Returns the short name entry with the up-to-date size and first cluster.
+    fn raw_dentry(&self) -> RawShortDentry {
+        let start_cluster = self.clusters.read().first().copied().unwrap_or(0);
+        let meta = self.meta.read();
+        let mut dentry = meta.dentry;
+        dentry.set_start_cluster(start_cluster);
+        dentry.size = match self.type_ {
+            InodeType::Dir => 0,
+            _ => meta.size as u32,
+        };
+        dentry
+    }
+
+    /// Writes the short name entry back to the parent directory.
+    fn sync_dentry(&self, _fs_guard: &MutexGuard<()>) -> Result<()> {
+        let Some(location) = self.location.read().clone() else {
+            return Ok(());
+        };
+        if self.meta.read().is_deleted {
+            return Ok(());
+        }
+        let dentry = self.raw_dentry();
+        location
+            .parent
+            .page_cache
+            .pages()
+            .write_val(location.offset, &dentry)?;
+        Ok(())
+    }
+
+    /// Updates the short name entry in memory and writes it back to the parent directory.
+    fn update_meta(&self, update: impl FnOnce(&mut InodeMeta, i64)) {
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        update(&mut self.meta.write(), fs.options().time_offset_secs());
+        if let Err(err) = self.sync_dentry(&fs_guard) {
+            warn!("failed to update the FAT directory entry: {:?}", err);
+        }
+    }
+
+    /// Updates the modification time and the change time after the contents are changed.
+    ///
+    /// The archive attribute of a file is also set, which tells backup tools that the file is
+    /// modified.
+    fn touch(&self, fs_guard: &MutexGuard<()>) -> Result<()> {
+        let fs = self.fs();
+        let time = now();
+        {
+            let mut meta = self.meta.write();
+            meta.dentry.set_modify_time(DosTimestamp::from_duration(
+                time,
+                fs.options().time_offset_secs(),
+            ));
+            meta.ctime = time;
+            if self.type_ == InodeType::File {
+                meta.dentry.attr |= FatAttr::ARCHIVE.bits();
+            }
+        }
+        self.sync_dentry(fs_guard)?;
+
+        if fs.options().flush && self.type_ == InodeType::Dir {
+            self.page_cache.evict_range(0..self.capacity())?;
+            fs.sync_meta()?;
+        }
+        Ok(())
+    }
+
+    /// Writes back the data and the short name entry.
+    pub(super) fn flush(&self) -> Result<()> {
+        self.page_cache
+            .evict_range(0..self.page_cache.pages().size())?;
+        {
+            let fs = self.fs();
+            let fs_guard = fs.lock();
+            self.sync_dentry(&fs_guard)?;
+        }
+        self.flush_dentry()
+    }
+
+    /// Writes back the page of the parent directory that holds the short name entry.
+    fn flush_dentry(&self) -> Result<()> {
+        let Some(location) = self.location.read().clone() else {
+            return Ok(());
+        };
+        location
+            .parent
+            .page_cache
+            .evict_range(location.offset..location.offset + DENTRY_SIZE)
+    }
+
+    /// Allocates `count` clusters at the end of the inode.
+    ///
+    /// If the inode had no clusters, the new first cluster is recorded after the short name
+    /// entry is synchronized.
+    fn alloc_clusters(&self, count: usize, fs_guard: &MutexGuard<()>) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        let fs = self.fs();
+        let last = self.clusters.read().last().copied();
+        let new_clusters = fs.alloc_clusters(last, count, fs_guard)?;
+        self.clusters.write().extend_from_slice(&new_clusters);
+        Ok(())
+    }
+
+    /// Allocates the clusters to hold `size` bytes.
+    fn reserve(&self, size: usize, fs_guard: &MutexGuard<()>) -> Result<()> {
+        let num_clusters = size.div_ceil(self.fs().super_block().cluster_size);
+        let count = num_clusters.saturating_sub(self.clusters.read().len());
+        self.alloc_clusters(count, fs_guard)
+    }
+
+    /// Frees the clusters beyond `size` bytes.
+    fn truncate_clusters(&self, size: usize, fs_guard: &MutexGuard<()>) -> Result<()> {
+        let fs = self.fs();
+        let num_clusters = size.div_ceil(fs.super_block().cluster_size);
+        let (freed, last) = {
+            let mut clusters = self.clusters.write();
+            if num_clusters >= clusters.len() {
+                return Ok(());
+            }
+            let freed = clusters.split_off(num_clusters);
+            (freed, clusters.last().copied())
+        };
+
+        // The remaining chain is terminated, or detached from the entry, before the clusters are
+        // freed, so the chain is always valid.
+        match last {
+            Some(last) => fs.write_fat(last, FatEntry::EndOfChain, fs_guard)?,
+            None => self.sync_dentry(fs_guard)?,
+        }
+        fs.free_clusters(&freed, fs_guard)
+    }
+
+    /// Frees all the clusters of a deleted inode, whose data are discarded.
+    pub(super) fn free_all_clusters(&self, fs_guard: &MutexGuard<()>) -> Result<()> {
+        debug_assert!(self.meta.read().is_deleted);
+        self.page_cache
+            .discard_range(0..self.page_cache.pages().size());
+        let clusters = core::mem::take(&mut *self.clusters.write());
+        self.fs().free_clusters(&clusters, fs_guard)
+    }
+
+    fn make_mode(&self, dentry: &RawShortDentry) -> InodeMode {
+        let fs = self.fs();
+        let options = fs.options();
+        let all = InodeMode::from_bits_truncate(0o777);
+        if self.type_ == InodeType::Dir {
+            return all - options.dmask;
+        }
+
+        let mut mode = all - options.fmask;
+        if dentry.attr().contains(FatAttr::READ_ONLY) {
+            mode -= WRITE_BITS;
+        }
+        if options.showexec && !matches!(&dentry.name[8..], b"EXE" | b"COM" | b"BAT") {
+            mode -= EXEC_BITS;
+        }
+        mode
+    }
+
+    /// Returns the cluster recorded by the `..` entries of the subdirectories.
+    fn dotdot_cluster(&self) -> ClusterId {
+        // The root directory is always referred to by cluster 0, even on FAT32 volumes.
+        if self.is_root() {
+            return 0;
+        }
+        self.clusters.read().first().copied().unwrap_or(0)
+    }
+
+    fn entries_from(&self, offset: usize) -> DirEntryIter<'_> {
+        let fs = self.fs();
+        let options = fs.options();
+        DentryReader::new(self.page_cache.pages(), self.capacity()).iter(
+            offset,
+            options.codepage,
+            options.shortname,
+        )
+    }
+
+    /// Finds the entry named `name`, ignoring `.` and `..`.
+    fn find_entry(&self, name: &str) -> Result<Option<DirEntry>> {
+        let codepage = self.fs().options().codepage;
+        for entry in self.entries_from(0) {
+            let entry = entry?;
+            if !entry.is_dot_or_dotdot() && entry.matches(name, codepage) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_empty_dir(&self) -> Result<bool> {
+        for entry in self.entries_from(0) {
+            if !entry?.is_dot_or_dotdot() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns whether this directory is `ancestor` or one of its descendants.
+    fn is_descendant_of(&self, ancestor: &VfatInode) -> bool {
+        let mut dir = self.this();
+        loop {
+            if core::ptr::eq(dir.as_ref(), ancestor) {
+                return true;
+            }
+            let parent = match dir.location.read().as_ref() {
+                Some(location) => location.parent.clone(),
+                None => return false,
+            };
+            dir = parent;
+        }
+    }
+
+    /// Returns the inode of the `entry` in this directory.
+    fn child_inode(&self, entry: &DirEntry, _fs_guard: &MutexGuard<()>) -> Result<Arc<Self>> {
+        let fs = self.fs();
+        let dentry_pos = self.device_offset(entry.offset);
+        if let Some(inode) = fs.find_inode(dentry_pos) {
+            return Ok(inode);
+        }
+
+        let sb = fs.super_block();
+        let clusters = fs.read_chain(entry.dentry.start_cluster(sb.fat_type))?;
+        let capacity = clusters.len() * sb.cluster_size;
+        let type_ = entry.type_();
+        // The size of a directory is not recorded, and the size of a file cannot exceed its
+        // clusters.
+        let size = match type_ {
+            InodeType::Dir => capacity,
+            _ => (entry.dentry.size as usize).min(capacity),
+        };
+
+        let inode = Self::new(
+            &fs,
+            (dentry_pos / DENTRY_SIZE) as u64,
+            type_,
+            Some(DentryLocation {
+                parent: self.this(),
+                offset: entry.offset,
+            }),
+            entry.dentry,
+            clusters,
+            size,
+        );
+        fs.insert_inode(dentry_pos, inode.clone());
+        Ok(inode)
+    }
+
+    /// Returns the inode number of the `entry` in this directory without loading the inode.
+    fn child_ino(&self, entry: &DirEntry) -> u64 {
+        let dentry_pos = self.device_offset(entry.offset);
+        match self.fs().find_inode(dentry_pos) {
+            Some(inode) => inode.ino,
+            None => (dentry_pos / DENTRY_SIZE) as u64,
+        }
+    }
+
+    /// Adds the entries of `name` that describe `dentry`, and returns the offset of the short
+    /// name entry.
+    ///
+    /// The short name and the case flags of `dentry` are updated.
+    fn add_entry(
+        &self,
+        name: &str,
+        dentry: &mut RawShortDentry,
+        fs_guard: &MutexGuard<()>,
+    ) -> Result<usize> {
+        let fs = self.fs();
+        let options = fs.options();
+
+        let mut short_names = Vec::new();
+        for entry in self.entries_from(0) {
+            short_names.push(entry?.short_name);
+        }
+        // A long name is required unless the name can be stored as a short name exactly.
+        let (short_name, case_flags, long_entries) =
+            match ShortName::from_exact_name(name, options.codepage, options.shortname) {
+                Some((short_name, case_flags)) if !short_names.contains(&short_name) => {
+                    (short_name, case_flags, Vec::new())
+                }
+                _ => {
+                    let short_name = ShortName::generate(name, options.codepage, |short_name| {
+                        short_names.contains(short_name)
+                    })?;
+                    (short_name, 0, short_name.long_entries(name))
+                }
+            };
+        dentry.name = *short_name.as_bytes();
+        dentry.case_flags = case_flags;
+
+        let num_slots = long_entries.len() + 1;
+        let offset =
+            DentryReader::new(self.page_cache.pages(), self.capacity()).find_free(num_slots)?;
+        let end = offset + num_slots * DENTRY_SIZE;
+        if end > self.capacity() {
+            self.extend_dir(end, fs_guard)?;
+        }
+
+        let mut buf = Vec::with_capacity(num_slots * DENTRY_SIZE);
+        for long_entry in long_entries.iter() {
+            buf.extend_from_slice(long_entry);
+        }
+        buf.extend_from_slice(dentry.as_bytes());
+        self.page_cache.pages().write_bytes(offset, &buf)?;
+
+        Ok(end - DENTRY_SIZE)
+    }
+
+    /// Extends the directory with zeroed clusters to hold `size` bytes.
+    fn extend_dir(&self, size: usize, fs_guard: &MutexGuard<()>) -> Result<()> {
+        if self.is_fixed_root() {
+            return_errno_with_message!(Errno::ENOSPC, "the root directory is full");
+        }
+        let new_capacity = size.align_up(self.fs().super_block().cluster_size);
+        if new_capacity > MAX_DIR_SIZE {
+            return_errno_with_message!(Errno::ENOSPC, "the directory is full");
+        }
+
+        let old_capacity = self.capacity();
+        self.reserve(new_capacity, fs_guard)?;
+        self.page_cache.resize(new_capacity)?;
+        self.page_cache.fill_zeros(old_capacity..new_capacity)?;
+        self.meta.write().size = new_capacity;
+        Ok(())
+    }
+
+    /// Marks the entries of `entry` as deleted.
+    fn clear_slots(&self, entry: &DirEntry) -> Result<()> {
+        for offset in entry.slots().step_by(DENTRY_SIZE) {
+            self.page_cache.pages().write_val(offset, &DELETED_MARKER)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the `entry` of the `inode` from this directory.
+    ///
+    /// The clusters of the inode are freed after the inode is no longer used.
+    fn remove_entry(
+        &self,
+        entry: &DirEntry,
+        inode: Arc<Self>,
+        fs_guard: &MutexGuard<()>,
+    ) -> Result<()> {
+        self.clear_slots(entry)?;
+
+        let fs = self.fs();
+        fs.remove_inode(self.device_offset(entry.offset));
+        inode.meta.write().is_deleted = true;
+        fs.add_orphan(inode, fs_guard)
+    }
+
+    /// Initializes a new directory with the `.` and `..` entries.
+    fn init_dir(&self, parent: &Self) -> Result<()> {
+        self.page_cache.fill_zeros(0..self.capacity())?;
+
+        let mut dot = self.raw_dentry();
+        dot.name = *ShortName::DOT.as_bytes();
+        dot.case_flags = 0;
+        let mut dotdot = dot;
+        dotdot.name = *ShortName::DOTDOT.as_bytes();
+        dotdot.set_start_cluster(parent.dotdot_cluster());
+
+        self.page_cache.pages().write_val(0, &dot)?;
+        self.page_cache.pages().write_val(DENTRY_SIZE, &dotdot)?;
+        Ok(())
+    }
+
+    /// Updates the `..` entry after the directory is moved to `new_parent`.
+    fn set_dotdot(&self, new_parent: &Self) -> Result<()> {
+        let mut dotdot: RawShortDentry = self.page_cache.pages().read_val(DENTRY_SIZE)?;
+        if dotdot.name != *ShortName::DOTDOT.as_bytes() {
+            warn!("the `..` entry of the FAT directory is missing");
+            return Ok(());
+        }
+        dotdot.set_start_cluster(new_parent.dotdot_cluster());
+        self.page_cache.pages().write_val(DENTRY_SIZE, &dotdot)?;
+        Ok(())
+    }
+}
+
+impl PageCacheBackend for VfatInode {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.fs().read_page_runs(frame, &self.device_runs(idx)?)
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.fs().write_page_runs(frame, &self.device_runs(idx)?)
+    }
+
+    fn npages(&self) -> usize {
+        self.capacity().align_up(PAGE_SIZE) / PAGE_SIZE
+    }
+}
+
+impl Inode for VfatInode {
+    fn size(&self) -> usize {
+        self.meta.read().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.type_ == InodeType::Dir {
+            return_errno!(Errno::EISDIR);
+        }
+        if new_size > MAX_FILE_SIZE {
+            return_errno_with_message!(Errno::EFBIG, "the file size exceeds the FAT limit");
+        }
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        let old_size = self.meta.read().size;
+        if new_size == old_size {
+            return Ok(());
+        }
+
+        // FAT does not support holes, so the extended part is filled with zeros.
+        if new_size > old_size {
+            self.reserve(new_size, &fs_guard)?;
+            self.page_cache.resize(new_size)?;
+            self.page_cache.fill_zeros(old_size..new_size)?;
+            self.meta.write().size = new_size;
+        } else {
+            self.page_cache.resize(new_size)?;
+            self.meta.write().size = new_size;
+            self.truncate_clusters(new_size, &fs_guard)?;
+        }
+        self.touch(&fs_guard)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let fs = self.fs();
+        let options = fs.options();
+        let time_offset = options.time_offset_secs();
+        let meta = self.meta.read();
+
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: meta.size,
+            blk_size: fs.super_block().cluster_size,
+            blocks: self.capacity() / 512,
+            atime: meta.dentry.access_time().as_duration(time_offset),
+            mtime: meta.dentry.modify_time().as_duration(time_offset),
+            ctime: meta.ctime,
+            type_: self.type_,
+            mode: self.make_mode(&meta.dentry),
+            nlinks: 1,
+            uid: options.uid,
+            gid: options.gid,
+            rdev: 0,
+        }
+    }
+
+    fn extended_metadata(&self) -> ExtendedMetadata {
+        let time_offset = self.fs().options().time_offset_secs();
+        let btime = self
+            .meta
+            .read()
+            .dentry
+            .create_time()
+            .as_duration(time_offset);
+        ExtendedMetadata {
+            btime: Some(btime),
+            ..Default::default()
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.make_mode(&self.meta.read().dentry))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        // Only the read-only attribute of files can be changed. Other changes are rejected
+        // unless the `quiet` option is specified.
+        if self.type_ == InodeType::File {
+            self.update_meta(|meta, _| {
+                if mode.intersects(WRITE_BITS) {
+                    meta.dentry.attr &= !FatAttr::READ_ONLY.bits();
+                } else {
+                    meta.dentry.attr |= FatAttr::READ_ONLY.bits();
+                }
+                meta.ctime = now();
+            });
+        } else if mode != self.mode()? && !self.fs().options().quiet {
+            return_errno_with_message!(Errno::EPERM, "the mode of FAT directories is fixed");
+        }
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.fs().options().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        let fs = self.fs();
+        if uid != fs.options().uid && !fs.options().quiet {
+            return_errno_with_message!(Errno::EPERM, "FAT does not record the owners");
+        }
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.fs().options().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        let fs = self.fs();
+        if gid != fs.options().gid && !fs.options().quiet {
+            return_errno_with_message!(Errno::EPERM, "FAT does not record the groups");
+        }
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        let time_offset = self.fs().options().time_offset_secs();
+        self.meta
+            .read()
+            .dentry
+            .access_time()
+            .as_duration(time_offset)
+    }
+
+    fn set_atime(&self, time: Duration) {
+        // Only the date is recorded, so the entry is rarely changed.
+        let time_offset = self.fs().options().time_offset_secs();
+        let timestamp = DosTimestamp::from_duration(time, time_offset);
+        if self.meta.read().dentry.access_time().date == timestamp.date {
+            return;
+        }
+        self.update_meta(|meta, _| meta.dentry.set_access_time(timestamp));
+    }
+
+    fn mtime(&self) -> Duration {
+        let time_offset = self.fs().options().time_offset_secs();
+        self.meta
+            .read()
+            .dentry
+            .modify_time()
+            .as_duration(time_offset)
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.update_meta(|meta, time_offset| {
+            meta.dentry
+                .set_modify_time(DosTimestamp::from_duration(time, time_offset))
+        });
+    }
+
+    fn ctime(&self) -> Duration {
+        self.meta.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.meta.write().ctime = time;
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        (self.type_ == InodeType::File).then(|| self.page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        if self.type_ == InodeType::Dir {
+            return_errno!(Errno::EISDIR);
+        }
+
+        let (read_offset, read_len) = {
+            let size = self.meta.read().size;
+            let start = size.min(offset);
+            let end = size.min(offset.saturating_add(writer.avail()));
+            (start, end - start)
+        };
+        self.page_cache
+            .pages()
+            .read(read_offset, writer.limit(read_len))?;
+
+        Ok(read_len)
+    }
+
+    // FAT does not support direct I/O, so it falls back to buffered I/O.
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        if self.type_ == InodeType::Dir {
+            return_errno!(Errno::EISDIR);
+        }
+        let write_len = reader.remain();
+        if write_len == 0 {
+            return Ok(0);
+        }
+        let end = offset
+            .checked_add(write_len)
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or_else(|| {
+                Error::with_message(Errno::EFBIG, "the file size exceeds the FAT limit")
+            })?;
+
+        let fs = self.fs();
+        {
+            let fs_guard = fs.lock();
+            // The page cache may be larger than the file if other writes are in progress. The
+            // part beyond the page cache is never written by them.
+            let cache_size = self.page_cache.pages().size();
+            if end > cache_size {
+                self.reserve(end, &fs_guard)?;
+                self.page_cache.resize(end)?;
+                self.page_cache
+                    .fill_zeros(cache_size..offset.max(cache_size))?;
+            }
+        }
+
+        // The lock is released, so that the writes of different files can be parallelized.
+        self.page_cache.pages().write(offset, reader)?;
+
+        {
+            let fs_guard = fs.lock();
+            {
+                let mut meta = self.meta.write();
+                meta.size = meta.size.max(end);
+            }
+            self.touch(&fs_guard)?;
+        }
+
+        if fs.options().flush {
+            self.page_cache.evict_range(offset..end)?;
+            self.flush_dentry()?;
+            fs.sync_meta()?;
+        }
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let write_len = self.write_at(offset, reader)?;
+        self.page_cache.evict_range(offset..offset + write_len)?;
+        Ok(write_len)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        if type_ != InodeType::File && type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "the file type is not supported by FAT");
+        }
+        if is_dot_or_dotdot(name) {
+            return_errno_with_message!(Errno::EEXIST, "the name is . or ..");
+        }
+        let name = check_long_name(name)?;
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        if self.meta.read().is_deleted {
+            return_errno_with_message!(Errno::ENOENT, "the directory has been removed");
+        }
+        if self.find_entry(name)?.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "the name already exists");
+        }
+
+        let mut dentry = RawShortDentry::default();
+        let timestamp = DosTimestamp::from_duration(now(), fs.options().time_offset_secs());
+        dentry.set_create_time(timestamp);
+        dentry.set_modify_time(timestamp);
+        dentry.set_access_time(timestamp);
+        let attr = if type_ == InodeType::Dir {
+            FatAttr::DIRECTORY
+        } else if mode.intersects(WRITE_BITS) {
+            FatAttr::ARCHIVE
+        } else {
+            FatAttr::ARCHIVE | FatAttr::READ_ONLY
+        };
+        dentry.attr = attr.bits();
+
+        // A directory always has a cluster, which holds the `.` and `..` entries.
+        let clusters = if type_ == InodeType::Dir {
+            fs.alloc_clusters(None, 1, &fs_guard)?
+        } else {
+            Vec::new()
+        };
+        if let Some(&cluster) = clusters.first() {
+            dentry.set_start_cluster(cluster);
+        }
+        let offset = match self.add_entry(name, &mut dentry, &fs_guard) {
+            Ok(offset) => offset,
+            Err(err) => {
+                fs.free_clusters(&clusters, &fs_guard)?;
+                return Err(err);
+            }
+        };
+
+        let dentry_pos = self.device_offset(offset);
+        let size = clusters.len() * fs.super_block().cluster_size;
+        let inode = Self::new(
+            &fs,
+            (dentry_pos / DENTRY_SIZE) as u64,
+            type_,
+            Some(DentryLocation {
+                parent: self.this(),
+                offset,
+            }),
+            dentry,
+            clusters,
+            size,
+        );
+        if type_ == InodeType::Dir {
+            inode.init_dir(self)?;
+        }
+        fs.insert_inode(dentry_pos, inode.clone());
+
+        self.touch(&fs_guard)?;
+        Ok(inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // The `.` and `..` entries are emitted first, since the root directory has none.
+            if *offset == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, 0)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", self.parent().ino, InodeType::Dir, 1)?;
+                *offset += 1;
+            }
+
+            for entry in self.entries_from(*offset - NUM_SPECIAL_ENTRIES) {
+                let entry = entry?;
+                if !entry.is_dot_or_dotdot() {
+                    visitor.visit(&entry.name, self.child_ino(&entry), entry.type_(), *offset)?;
+                }
+                *offset = entry.offset + DENTRY_SIZE + NUM_SPECIAL_ENTRIES;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        if is_dot_or_dotdot(name) {
+            return_errno_with_message!(Errno::EISDIR, "the name is . or ..");
+        }
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        let entry = self
+            .find_entry(name)?
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        if entry.type_() == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "the inode is a directory");
+        }
+
+        let inode = self.child_inode(&entry, &fs_guard)?;
+        self.remove_entry(&entry, inode, &fs_guard)?;
+        self.touch(&fs_guard)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        if is_dot(name) {
+            return_errno_with_message!(Errno::EINVAL, "rmdir on .");
+        }
+        if name == ".." {
+            return_errno_with_message!(Errno::ENOTEMPTY, "rmdir on ..");
+        }
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        let entry = self
+            .find_entry(name)?
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        if entry.type_() != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+
+        let inode = self.child_inode(&entry, &fs_guard)?;
+        if !inode.is_empty_dir()? {
+            return_errno_with_message!(Errno::ENOTEMPTY, "the directory is not empty");
+        }
+        self.remove_entry(&entry, inode, &fs_guard)?;
+        self.touch(&fs_guard)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        if is_dot(name) {
+            return Ok(self.this());
+        }
+        if name == ".." {
+            return Ok(self.parent());
+        }
+
+        // The trailing dots are ignored, which is the same as how the names are created.
+        let name = match name.trim_end_matches('.') {
+            "" => name,
+            trimmed => trimmed,
+        };
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        let entry = self
+            .find_entry(name)?
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        let inode = self.child_inode(&entry, &fs_guard)?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if is_dot_or_dotdot(old_name) || is_dot_or_dotdot(new_name) {
+            return_errno_with_message!(Errno::EISDIR, "the name is . or ..");
+        }
+        let target = target
+            .downcast_ref::<VfatInode>()
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not same fs"))?;
+        if !self.fs.ptr_eq(&target.fs) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        self.check_dir()?;
+        target.check_dir()?;
+        let new_name = check_long_name(new_name)?;
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        if target.meta.read().is_deleted {
+            return_errno_with_message!(Errno::ENOENT, "the directory has been removed");
+        }
+        let old_entry = self
+            .find_entry(old_name)?
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the name does not exist"))?;
+        let inode = self.child_inode(&old_entry, &fs_guard)?;
+        let is_same_dir = core::ptr::eq(self, target);
+        if inode.type_ == InodeType::Dir && !is_same_dir && target.is_descendant_of(&inode) {
+            return_errno_with_message!(Errno::EINVAL, "the directory cannot be moved into itself");
+        }
+
+        let mut dentry = inode.raw_dentry();
+        let new_offset = match target.find_entry(new_name)? {
+            // The names refer to the same entry, which is renamed in place to change the case.
+            Some(new_entry) if is_same_dir && new_entry.offset == old_entry.offset => {
+                if old_entry.name == new_name {
+                    return Ok(());
+                }
+                self.clear_slots(&old_entry)?;
+                self.add_entry(new_name, &mut dentry, &fs_guard)?
+            }
+            new_entry => {
+                if let Some(new_entry) = new_entry {
+                    let new_inode = target.child_inode(&new_entry, &fs_guard)?;
+                    match (inode.type_, new_inode.type_) {
+                        (InodeType::Dir, InodeType::Dir) => {
+                            if !new_inode.is_empty_dir()? {
+                                return_errno_with_message!(
+                                    Errno::ENOTEMPTY,
+                                    "the target directory is not empty"
+                                );
+                            }
+                        }
+                        (InodeType::Dir, _) => {
+                            return_errno_with_message!(
+                                Errno::ENOTDIR,
+                                "the target is not a directory"
+                            )
+                        }
+                        (_, InodeType::Dir) => {
+                            return_errno_with_message!(Errno::EISDIR, "the target is a directory")
+                        }
+                        _ => (),
+                    }
+                    target.remove_entry(&new_entry, new_inode, &fs_guard)?;
+                }
+                // The new entry is added before the old one is removed, so the inode is not lost
+                // if the operation fails.
+                let new_offset = target.add_entry(new_name, &mut dentry, &fs_guard)?;
+                self.clear_slots(&old_entry)?;
+                new_offset
+            }
+        };
+
+        fs.remove_inode(self.device_offset(old_entry.offset));
+        fs.insert_inode(target.device_offset(new_offset), inode.clone());
+        {
+            let mut meta = inode.meta.write();
+            meta.dentry.name = dentry.name;
+            meta.dentry.case_flags = dentry.case_flags;
+            meta.ctime = now();
+        }
+        *inode.location.write() = Some(DentryLocation {
+            parent: target.this(),
+            offset: new_offset,
+        });
+        if inode.type_ == InodeType::Dir && !is_same_dir {
+            inode.set_dotdot(target)?;
+        }
+
+        self.touch(&fs_guard)?;
+        if !is_same_dir {
+            target.touch(&fs_guard)?;
+        }
+        Ok(())
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.flush()?;
+        self.fs().sync_meta()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.page_cache
+            .evict_range(0..self.page_cache.pages().size())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+}
+
+impl Debug for VfatInode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("VfatInode")
+            .field("ino", &self.ino)
+            .field("type_", &self.type_)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The FAT file system with long file names (VFAT).
+//!
+//! FAT is the file system of EFI system partitions, USB sticks, and SD cards. This driver
+//! supports FAT12, FAT16, and FAT32 volumes.
+//!
+//! The features are as follows:
+//! 1. Reading and writing files and directories, including renaming and truncating.
+//! 2. Long file names, which are stored in UTF-16 and looked up case-insensitively. The short
+//!    names are generated with numeric tails (e.g., `LONGFI~1.TXT`) and encoded in a code page.
+//! 3. The creation, modification, and access times, which are stored in the local time.
+//! 4. The mount options of Linux's vfat, e.g., `uid`, `gid`, `umask`, `fmask`, `dmask`,
+//!    `codepage`, `shortname`, `time_offset`, `showexec`, `quiet`, and `flush`.
+//!
+//! # Limitation
+//!
+//! Here we summarizes the features that need to be implemented in the future.
+//! 1. Supports the exFAT volumes with this driver. They are handled by the separate exFAT
+//!    driver now.
+//! 2. Supports the character sets other than UTF-8 for the long names.
+//! 3. Compacts the directories after the entries are removed.
+//! 4. Sets the volume dirty bit when the volume is mounted.
+
+pub use fs::{VfatFS, VfatMountOptions};
+pub use inode::VfatInode;
+
+mod codepage;
+mod dentry;
+mod fat;
+mod fs;
+mod inode;
+mod super_block;
+mod utils;
+
+#[cfg(ktest)]
+mod test {
+    use aster_block::BlockDevice;
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        fs::utils::{
+            check_crash_consistency, run_benchmarks, FileSystem, FsExerciser, Inode, InodeMode,
+            InodeType, MemoryDisk,
+        },
+        prelude::*,
+        process::{Gid, Uid},
+    };
+
+    const SECTOR_SIZE: usize = 512;
+
+    /// Creates an empty FAT16 or FAT32 image, like `mkfs.vfat`.
+    fn new_image(size: usize, sectors_per_cluster: usize, is_fat32: bool) -> Vec<u8> {
+        let mut image = vec![0u8; size];
+        let put = |image: &mut Vec<u8>, offset: usize, bytes: &[u8]| {
+            image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+
+        let total_sectors = size / SECTOR_SIZE;
+        let reserved_sectors: u16 = if is_fat32 { 32 } else { 1 };
+        let root_entries: u16 = if is_fat32 { 0 } else { 512 };
+        let entry_size = if is_fat32 { 4 } else { 2 };
+        // The clusters are overestimated, so the FATs are large enough.
+        let fat_sectors =
+            ((total_sectors / sectors_per_cluster + 2) * entry_size).div_ceil(SECTOR_SIZE) as u32;
+
+        put(&mut image, 0, &[0xEB, 0x58, 0x90]);
+        put(&mut image, 3, b"MSWIN4.1");
+        put(&mut image, 11, &(SECTOR_SIZE as u16).to_le_bytes());
+        put(&mut image, 13, &[sectors_per_cluster as u8]);
+        put(&mut image, 14, &reserved_sectors.to_le_bytes());
+        put(&mut image, 16, &[2]);
+        put(&mut image, 17, &root_entries.to_le_bytes());
+        put(&mut image, 21, &[0xF8]);
+        put(&mut image, 32, &(total_sectors as u32).to_le_bytes());
+        put(&mut image, 510, &[0x55, 0xAA]);
+
+        let fat_start = reserved_sectors as usize * SECTOR_SIZE;
+        let fat_size = fat_sectors as usize * SECTOR_SIZE;
+        if is_fat32 {
+            put(&mut image, 36, &fat_sectors.to_le_bytes());
+            // The root directory is in cluster 2.
+            put(&mut image, 44, &2u32.to_le_bytes());
+            // The FSInfo sector is sector 1, whose hints are unknown.
+            put(&mut image, 48, &1u16.to_le_bytes());
+            put(&mut image, 66, &[0x29]);
+            put(&mut image, 67, &0x1234_5678u32.to_le_bytes());
+            put(&mut image, SECTOR_SIZE, &0x4161_5252u32.to_le_bytes());
+            put(&mut image, SECTOR_SIZE + 484, &0x6141_7272u32.to_le_bytes());
+            put(&mut image, SECTOR_SIZE + 488, &[0xFF; 8]);
+            put(&mut image, SECTOR_SIZE + 508, &0xAA55_0000u32.to_le_bytes());
+            for fat_index in 0..2 {
+                let entries = [0x0FFF_FFF8u32, 0x0FFF_FFFF, 0x0FFF_FFFF];
+                for (index, entry) in entries.iter().enumerate() {
+                    let offset = fat_start + fat_index * fat_size + index * 4;
+                    put(&mut image, offset, &entry.to_le_bytes());
+                }
+            }
+        } else {
+            put(&mut image, 22, &(fat_sectors as u16).to_le_bytes());
+            put(&mut image, 38, &[0x29]);
+            put(&mut image, 39, &0x1234_5678u32.to_le_bytes());
+            for fat_index in 0..2 {
+                let offset = fat_start + fat_index * fat_size;
+                put(&mut image, offset, &[0xF8, 0xFF, 0xFF, 0xFF]);
+            }
+        }
+
+        image
+    }
+
+    fn new_fat16_disk() -> Arc<MemoryDisk> {
+        // The clusters are smaller than pages, so a page is backed by several clusters.
+        MemoryDisk::from_image(&new_image(16 * 1024 * 1024, 4, false))
+    }
+
+    fn new_fat32_disk() -> Arc<MemoryDisk> {
+        MemoryDisk::from_image(&new_image(16 * 1024 * 1024, 8, true))
+    }
+
+    fn open(disk: Arc<dyn BlockDevice>, options: &str) -> Arc<VfatFS> {
+        crate::time::clocks::init_for_ktest();
+        VfatFS::open(disk, VfatMountOptions::parse(options).unwrap()).unwrap()
+    }
+
+    fn list(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names = Vec::new();
+        dir.readdir_at(0, &mut names).unwrap();
+        names
+    }
+
+    #[ktest]
+    fn long_and_short_names() {
+        let fs = open(new_fat16_disk(), "");
+        let root = fs.root_inode();
+        let mode = InodeMode::from_bits_truncate(0o644);
+
+        root.create("README.TXT", InodeType::File, mode).unwrap();
+        root.create("A long file name.txt", InodeType::File, mode)
+            .unwrap();
+        root.create("A long file name.md", InodeType::File, mode)
+            .unwrap();
+        root.create("Mixed", InodeType::Dir, mode).unwrap();
+
+        let names = list(&root);
+        for name in [
+            "README.TXT",
+            "A long file name.txt",
+            "A long file name.md",
+            "Mixed",
+        ] {
+            assert!(names.iter().any(|n| n == name), "{} is not listed", name);
+        }
+
+        // The names are case-insensitive, and the short names of long names can be used.
+        root.lookup("readme.txt").unwrap();
+        root.lookup("a LONG file NAME.TXT").unwrap();
+        root.lookup("ALONGF~1.TXT").unwrap();
+        root.lookup("ALONGF~1.MD").unwrap();
+        root.lookup("mixed").unwrap();
+        assert_eq!(
+            root.create("readme.txt", InodeType::File, mode)
+                .unwrap_err()
+                .error(),
+            Errno::EEXIST
+        );
+        assert_eq!(
+            root.create("a:b", InodeType::File, mode)
+                .unwrap_err()
+                .error(),
+            Errno::EINVAL
+        );
+
+        // A case-only rename changes the displayed name.
+        root.rename("mixed", &root, "MIXED").unwrap();
+        assert!(list(&root).iter().any(|n| n == "MIXED"));
+    }
+
+    #[ktest]
+    fn remount() {
+        let disk = new_fat32_disk();
+        let data = b"Hello, FAT!";
+        {
+            let fs = open(disk.clone(), "");
+            let root = fs.root_inode();
+            let dir = root
+                .create("Some directory", InodeType::Dir, InodeMode::all())
+                .unwrap();
+            let file = dir
+                .create("some file.bin", InodeType::File, InodeMode::all())
+                .unwrap();
+            file.write_bytes_at(8192, data).unwrap();
+            root.create("empty", InodeType::File, InodeMode::all())
+                .unwrap();
+            root.unlink("empty").unwrap();
+            fs.sync().unwrap();
+        }
+
+        let fs = open(disk, "");
+        let root = fs.root_inode();
+        assert!(root.lookup("empty").is_err());
+        let dir = root.lookup("some directory").unwrap();
+        assert_eq!(list(&dir), vec![".", "..", "some file.bin"]);
+        let file = dir.lookup("some file.bin").unwrap();
+        assert_eq!(file.size(), 8192 + data.len());
+
+        let mut buf = vec![0xFFu8; file.size()];
+        file.read_bytes_at(0, &mut buf).unwrap();
+        assert!(buf[..8192].iter().all(|&byte| byte == 0));
+        assert_eq!(&buf[8192..], data);
+    }
+
+    #[ktest]
+    fn mount_options() {
+        let fs = open(
+            new_fat16_disk(),
+            "uid=1000,gid=100,fmask=022,dmask=077,showexec",
+        );
+        let root = fs.root_inode();
+        let file = root
+            .create("run.exe", InodeType::File, InodeMode::all())
+            .unwrap();
+        let text = root
+            .create("text", InodeType::File, InodeMode::all())
+            .unwrap();
+        let dir = root
+            .create("dir", InodeType::Dir, InodeMode::all())
+            .unwrap();
+
+        assert_eq!(file.metadata().uid, Uid::new(1000));
+        assert_eq!(file.metadata().gid, Gid::new(100));
+        assert_eq!(file.mode().unwrap().bits(), 0o755);
+        assert_eq!(text.mode().unwrap().bits(), 0o644);
+        assert_eq!(dir.mode().unwrap().bits(), 0o700);
+
+        // The write bits are cleared by the read-only attribute.
+        text.set_mode(InodeMode::from_bits_truncate(0o444)).unwrap();
+        assert_eq!(text.mode().unwrap().bits(), 0o444);
+
+        for options in [
+            "iocharset=iso8859-1",
+            "codepage=932",
+            "umask=999",
+            "unknown",
+        ] {
+            assert_eq!(
+                VfatMountOptions::parse(options).unwrap_err().error(),
+                Errno::EINVAL
+            );
+        }
+    }
+
+    #[ktest]
+    fn exerciser() {
+        for disk in [new_fat16_disk(), new_fat32_disk()] {
+            let fs = open(disk, "");
+            FsExerciser::new(&fs.root_inode(), 0).run(500);
+        }
+    }
+
+    #[ktest]
+    fn crash_consistency() {
+        check_crash_consistency(
+            new_fat16_disk(),
+            |disk| -> Arc<dyn FileSystem> { open(disk, "") },
+            0,
+            200,
+        );
+    }
+
+    #[ktest]
+    fn benchmarks() {
+        let fs = open(new_fat32_disk(), "");
+        run_benchmarks(&fs.root_inode());
+    }
+}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::fat::{ClusterId, FatType, FIRST_DATA_CLUSTER};
+use crate::prelude::*;
+
+/// The magic number reported by `statfs`, which is the same as Linux's `MSDOS_SUPER_MAGIC`.
+pub(super) const VFAT_MAGIC: u64 = 0x4d44;
+
+/// The maximum cluster count of FAT12 volumes.
+const MAX_FAT12_CLUSTERS: u32 = 4084;
+/// The maximum cluster count of FAT16 volumes.
+const MAX_FAT16_CLUSTERS: u32 = 65524;
+/// The maximum cluster count of FAT32 volumes, as the upper 4 bits of the entries are reserved.
+const MAX_FAT32_CLUSTERS: u32 = 0x0FFF_FFF5;
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FSINFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+/// The value of the FSInfo fields that are unknown.
+const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The BIOS parameter block in the boot sector, which is shared by all FAT types.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct RawBootSector {
+    jmp_boot: [u8; 3],
+    oem_name: [u8; 8],
+    bytes_per_sector: u16,
+    sectors_per_cluster: u8,
+    reserved_sectors: u16,
+    num_fats: u8,
+    root_entries: u16,
+    total_sectors_16: u16,
+    media: u8,
+    fat_size_16: u16,
+    sectors_per_track: u16,
+    num_heads: u16,
+    hidden_sectors: u32,
+    total_sectors_32: u32,
+    /// The extended BIOS parameter block of FAT32.
+    fat32: RawFat32Bpb,
+}
+
+/// The extended BIOS parameter block of FAT32.
+///
+/// The FAT12/16 volumes store their extended boot signatures here instead, which are not used.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawFat32Bpb {
+    fat_size_32: u32,
+    ext_flags: u16,
+    fs_version: u16,
+    root_cluster: u32,
+    fs_info_sector: u16,
+    backup_boot_sector: u16,
+    reserved: [u8; 12],
+    drive_number: u8,
+    reserved1: u8,
+    boot_signature: u8,
+    volume_id: u32,
+    volume_label: [u8; 11],
+    fs_type: [u8; 8],
+}
+
+/// If this bit of `ext_flags` is set, only the active FAT is used instead of mirroring all FATs.
+const EXT_FLAGS_NO_MIRRORING: u16 = 1 << 7;
+/// The index of the active FAT in `ext_flags`.
+const EXT_FLAGS_ACTIVE_FAT_MASK: u16 = 0x0F;
+
+/// The FSInfo sector of FAT32, which caches the allocation hints.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct RawFsInfo {
+    lead_signature: u32,
+    reserved1: [u8; 480],
+    struct_signature: u32,
+    free_count: u32,
+    next_free: u32,
+    reserved2: [u8; 12],
+    trail_signature: u32,
+}
+
+impl RawFsInfo {
+    /// Returns the number of the free clusters, if it is recorded.
+    pub(super) fn free_count(&self) -> Option<u32> {
+        if !self.is_valid() || self.free_count == FSINFO_UNKNOWN {
+            return None;
+        }
+        Some(self.free_count)
+    }
+
+    /// Returns the hint where to search for the free clusters, if it is recorded.
+    pub(super) fn next_free(&self) -> Option<ClusterId> {
+        if !self.is_valid() || self.next_free == FSINFO_UNKNOWN {
+            return None;
+        }
+        Some(self.next_free)
+    }
+
+    /// Records the allocation hints. The sector is left untouched if it is invalid.
+    pub(super) fn set_hints(&mut self, free_count: u32, next_free: ClusterId) {
+        if !self.is_valid() {
+            return;
+        }
+        self.free_count = free_count;
+        self.next_free = next_free;
+    }
+
+    fn is_valid(&self) -> bool {
+        self.lead_signature == FSINFO_LEAD_SIGNATURE
+            && self.struct_signature == FSINFO_STRUCT_SIGNATURE
+            && self.trail_signature == FSINFO_TRAIL_SIGNATURE
+    }
+}
+
+/// The in-memory geometry of a FAT volume, in which all the offsets are in bytes.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct SuperBlock {
+    pub(super) fat_type: FatType,
+    pub(super) sector_size: usize,
+    pub(super) cluster_size: usize,
+    /// The offset of the first FAT.
+    pub(super) fat_start: usize,
+    /// The size of each FAT.
+    pub(super) fat_size: usize,
+    pub(super) num_fats: usize,
+    /// The index of the only FAT that is used, or `None` if all FATs are mirrored.
+    pub(super) active_fat: Option<usize>,
+    /// The offset of the root directory of FAT12/16, which has a fixed size.
+    pub(super) root_dir_start: usize,
+    /// The size of the root directory of FAT12/16, which is zero for FAT32.
+    pub(super) root_dir_size: usize,
+    /// The first cluster of the root directory of FAT32.
+    pub(super) root_cluster: ClusterId,
+    /// The offset of the first data cluster.
+    pub(super) data_start: usize,
+    /// The number of the data clusters.
+    pub(super) num_clusters: u32,
+    /// The offset of the FSInfo sector of FAT32.
+    pub(super) fs_info: Option<usize>,
+    pub(super) volume_id: u32,
+}
+
+impl TryFrom<RawBootSector> for SuperBlock {
+    type Error = crate::error::Error;
+
+    fn try_from(raw: RawBootSector) -> Result<Self> {
+        let sector_size = raw.bytes_per_sector as usize;
+        if !sector_size.is_power_of_two() || !(512..=4096).contains(&sector_size) {
+            return_errno_with_message!(Errno::EINVAL, "invalid sector size");
+        }
+        let sectors_per_cluster = raw.sectors_per_cluster as usize;
+        if !sectors_per_cluster.is_power_of_two() {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of sectors per cluster");
+        }
+        if raw.reserved_sectors == 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of reserved sectors");
+        }
+        if raw.num_fats == 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of FATs");
+        }
+        // Only the media descriptors that are defined by the specification are allowed.
+        if raw.media != 0xF0 && raw.media < 0xF8 {
+            return_errno_with_message!(Errno::EINVAL, "invalid media descriptor");
+        }
+
+        let is_fat32 = raw.fat_size_16 == 0;
+        let fat_sectors = if is_fat32 {
+            raw.fat32.fat_size_32 as usize
+        } else {
+            raw.fat_size_16 as usize
+        };
+        let total_sectors = if raw.total_sectors_16 != 0 {
+            raw.total_sectors_16 as usize
+        } else {
+            raw.total_sectors_32 as usize
+        };
+        if fat_sectors == 0 || total_sectors == 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid volume size");
+        }
+        if is_fat32 && raw.root_entries != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the root directory of FAT32 is not fixed");
+        }
+
+        let fat_start = raw.reserved_sectors as usize * sector_size;
+        let fat_size = fat_sectors * sector_size;
+        let root_dir_start = fat_start + fat_size * raw.num_fats as usize;
+        let root_dir_size = (raw.root_entries as usize * super::dentry::DENTRY_SIZE)
+            .div_ceil(sector_size)
+            * sector_size;
+        let data_start = root_dir_start + root_dir_size;
+        let total_size = total_sectors * sector_size;
+        if data_start >= total_size {
+            return_errno_with_message!(Errno::EINVAL, "the volume has no data clusters");
+        }
+
+        let cluster_size = sectors_per_cluster * sector_size;
+        let num_clusters = ((total_size - data_start) / cluster_size) as u32;
+        let fat_type = if is_fat32 {
+            FatType::Fat32
+        } else if num_clusters <= MAX_FAT12_CLUSTERS {
+            FatType::Fat12
+        } else {
+            FatType::Fat16
+        };
+        let max_clusters = match fat_type {
+            FatType::Fat12 => MAX_FAT12_CLUSTERS,
+            FatType::Fat16 => MAX_FAT16_CLUSTERS,
+            FatType::Fat32 => MAX_FAT32_CLUSTERS,
+        };
+        if num_clusters == 0 || num_clusters > max_clusters {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of clusters");
+        }
+        // The FAT must be able to hold the entries of the reserved clusters and the data clusters.
+        if fat_type.entries_size(num_clusters + FIRST_DATA_CLUSTER) > fat_size {
+            return_errno_with_message!(Errno::EINVAL, "the FAT is too small");
+        }
+
+        let (active_fat, root_cluster, fs_info, volume_id) = match fat_type {
+            FatType::Fat32 => {
+                let ext_flags = raw.fat32.ext_flags;
+                let active_fat = if ext_flags & EXT_FLAGS_NO_MIRRORING != 0 {
+                    let index = (ext_flags & EXT_FLAGS_ACTIVE_FAT_MASK) as usize;
+                    if index >= raw.num_fats as usize {
+                        return_errno_with_message!(Errno::EINVAL, "invalid active FAT");
+                    }
+                    Some(index)
+                } else {
+                    None
+                };
+                let root_cluster = raw.fat32.root_cluster;
+                if root_cluster < FIRST_DATA_CLUSTER
+                    || root_cluster >= num_clusters + FIRST_DATA_CLUSTER
+                {
+                    return_errno_with_message!(Errno::EINVAL, "invalid root cluster");
+                }
+                let fs_info_sector = raw.fat32.fs_info_sector as usize;
+                let fs_info = (fs_info_sector != 0
+                    && fs_info_sector < raw.reserved_sectors as usize)
+                    .then_some(fs_info_sector * sector_size);
+                (active_fat, root_cluster, fs_info, raw.fat32.volume_id)
+            }
+            // The volume ID of FAT12/16 is located at a different offset in the extended BPB.
+            _ => {
+                let raw_bytes = raw.as_bytes();
+                let volume_id = u32::from_le_bytes(raw_bytes[39..43].try_into().unwrap());
+                (None, 0, None, volume_id)
+            }
+        };
+
+        Ok(Self {
+            fat_type,
+            sector_size,
+            cluster_size,
+            fat_start,
+            fat_size,
+            num_fats: raw.num_fats as usize,
+            active_fat,
+            root_dir_start,
+            root_dir_size,
+            root_cluster,
+            data_start,
+            num_clusters,
+            fs_info,
+            volume_id,
+        })
+    }
+}
+
+impl SuperBlock {
+    /// Returns the offset of the first byte of the `cluster`.
+    pub(super) fn cluster_offset(&self, cluster: ClusterId) -> usize {
+        self.data_start + (cluster - FIRST_DATA_CLUSTER) as usize * self.cluster_size
+    }
+
+    /// Returns whether the `cluster` is a data cluster of the volume.
+    pub(super) fn is_valid_cluster(&self, cluster: ClusterId) -> bool {
+        (FIRST_DATA_CLUSTER..self.num_clusters + FIRST_DATA_CLUSTER).contains(&cluster)
+    }
+
+    /// Returns the offset where the FATs end.
+    pub(super) fn fat_end(&self) -> usize {
+        self.fat_start + self.fat_size * self.num_fats
+    }
+
+    /// Returns the total size of the data clusters.
+    pub(super) fn data_size(&self) -> usize {
+        self.num_clusters as usize * self.cluster_size
+    }
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::prelude::*;
+
+/// The earliest time that can be represented, which is 1980-01-01 00:00:00.
+const DOS_EPOCH_SECS: i64 = 315_532_800;
+/// The latest time that can be represented, which is 2107-12-31 23:59:58.
+const DOS_MAX_SECS: i64 = 4_354_819_198;
+
+/// A timestamp in the directory entries.
+///
+/// The timestamps are stored in the local time. The offset from UTC is specified by the
+/// `time_offset` mount option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct DosTimestamp {
+    /// The date, with the year since 1980 in bits 9-15, the month in bits 5-8, and the day in
+    /// bits 0-4.
+    pub(super) date: u16,
+    /// The time, with the hour in bits 11-15, the minute in bits 5-10, and the second divided
+    /// by 2 in bits 0-4.
+    pub(super) time: u16,
+    /// The count of 10 ms within the two seconds, ranging from 0 to 199.
+    pub(super) centis: u8,
+}
+
+impl DosTimestamp {
+    /// Converts a duration since the UNIX epoch to the timestamp.
+    ///
+    /// The times that cannot be represented are clamped.
+    pub(super) fn from_duration(duration: Duration, time_offset_secs: i64) -> Self {
+        let secs = (duration.as_secs() as i64).saturating_add(time_offset_secs);
+        let (secs, nanos) = if secs < DOS_EPOCH_SECS {
+            (DOS_EPOCH_SECS, 0)
+        } else if secs > DOS_MAX_SECS {
+            (DOS_MAX_SECS, 0)
+        } else {
+            (secs, duration.subsec_nanos())
+        };
+        // The clamped value is always in the range.
+        let date_time = OffsetDateTime::from_unix_timestamp(secs).unwrap();
+
+        let date = (((date_time.year() - 1980) as u16) << 9)
+            | ((date_time.month() as u16) << 5)
+            | date_time.day() as u16;
+        let time = ((date_time.hour() as u16) << 11)
+            | ((date_time.minute() as u16) << 5)
+            | (date_time.second() as u16 >> 1);
+        let centis = ((date_time.second() as u32 % 2) * 100 + nanos / 10_000_000) as u8;
+
+        Self { date, time, centis }
+    }
+
+    /// Converts the timestamp to a duration since the UNIX epoch.
+    ///
+    /// The invalid fields are treated as the minimum valid values, like other implementations.
+    pub(super) fn as_duration(&self, time_offset_secs: i64) -> Duration {
+        let year = 1980 + (self.date >> 9) as i32;
+        let month = Month::try_from(((self.date >> 5) & 0xF) as u8).unwrap_or(Month::January);
+        let day = ((self.date & 0x1F) as u8).max(1);
+        let date = Date::from_calendar_date(year, month, day)
+            .or_else(|_| Date::from_calendar_date(year, month, 1))
+            .unwrap();
+
+        let hour = ((self.time >> 11) as u8).min(23);
+        let minute = (((self.time >> 5) & 0x3F) as u8).min(59);
+        let second = (((self.time & 0x1F) as u8) * 2).min(58);
+        let time = Time::from_hms(hour, minute, second).unwrap();
+
+        let secs = PrimitiveDateTime::new(date, time)
+            .assume_utc()
+            .unix_timestamp()
+            .saturating_sub(time_offset_secs)
+            .max(0);
+        let centis = self.centis.min(199) as u64;
+        Duration::from_secs(secs as u64 + centis / 100) + Duration::from_millis(centis % 100 * 10)
+    }
+}
+
+/// Returns the current time.
+pub(super) fn now() -> Duration {
+    crate::time::clocks::RealTimeCoarseClock::get().read_time()
+}
+
+/// Computes the checksum of a short name, which is stored in its long name entries.
+pub(super) fn short_name_checksum(name: &[u8; 11]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The kernel configuration.
+//!
+//! The OSDK resolves the kernel configuration options declared in `OSDK.toml` into Cargo
+//! features, and passes the effective configuration to the build in the format of the `.config`
+//! file of Linux. The configuration is embedded in the kernel image and reported at runtime via
+//! `/proc/config`.
+
+/// The effective kernel configuration.
+///
+/// The configuration is empty if the kernel is not built by the OSDK.
+pub const KCONFIG: &str = match option_env!("OSDK_KCONFIG") {
+    Some(kconfig) => kconfig,
+    None => "",
+};
+
+/// Returns whether the kernel configuration option is enabled.
+///
+/// The name of the option does not include the `CONFIG_` prefix.
+pub fn is_enabled(name: &str) -> bool {
+    KCONFIG.lines().any(|line| {
+        line.strip_prefix("CONFIG_")
+            .and_then(|line| line.strip_suffix("=y"))
+            .is_some_and(|option| option == name)
+    })
+}
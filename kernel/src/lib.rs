@@ -57,6 +57,7 @@ pub mod events;
 pub mod fs;
 pub mod ipc;
 pub mod kcmdline;
+pub mod kconfig;
 pub mod net;
 pub mod prelude;
 mod process;
@@ -133,6 +134,7 @@ fn init_thread() {
     net::lazy_init();
     fs::lazy_init();
     ipc::init();
+    sched::watchdog::init();
     // driver::pci::virtio::block::block_device_test();
     let thread = ThreadOptions::new(|| {
         println!("[kernel] Hello world from kernel!");
@@ -0,0 +1,585 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Software bridges.
+//!
+//! A bridge connects multiple ifaces (i.e., the ports of the bridge) at the link layer, so that
+//! the hosts behind different ports can talk to each other as if they were on the same link.
+//! Like Linux, the bridge learns which port each Ethernet address is behind from the source
+//! addresses of the received frames, forwards the frames to the ports behind which their
+//! destination addresses are learned, and floods the other frames to all the ports.
+//!
+//! The bridge is also an iface itself. It can have IP addresses and receive the frames that are
+//! destined for it.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/bridge>.
+
+use alloc::{borrow::ToOwned, sync::Arc};
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_bigtcp::{
+    device::{Device, DeviceCapabilities, Medium, NotifyDevice, RxToken, TxToken, WithDevice},
+    iface::{EtherIface, InterfaceFlags, InterfaceType, RxHandler},
+    time::Instant,
+    wire::EthernetAddress,
+};
+use aster_softirq::BottomHalfDisabled;
+use aster_time::read_monotonic_time;
+use spin::Once;
+
+use super::{
+    init::{register_iface, unregister_iface},
+    iter_all_ifaces,
+    sched::PollScheduler,
+    Iface,
+};
+use crate::{prelude::*, util::random::getrandom};
+
+/// A software bridge.
+pub struct Bridge {
+    ether_addr: EthernetAddress,
+    iface: Once<Weak<Iface>>,
+    ports: SpinLock<Vec<Port>, BottomHalfDisabled>,
+    /// The forwarding database, which maps the learned addresses to the ports.
+    fdb: SpinLock<BTreeMap<EthernetAddress, FdbEntry>, BottomHalfDisabled>,
+    /// The time after which the learned addresses are forgotten, in milliseconds.
+    ageing_time_ms: AtomicU64,
+    /// The frames that are destined for the bridge itself.
+    rx_queue: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+}
+
+/// A port of a bridge.
+struct Port {
+    iface: Arc<Iface>,
+    ether_addr: EthernetAddress,
+    /// Whether the port is blocked if it receives a BPDU.
+    ///
+    /// This is known as "BPDU guard" in Linux. Since STP is not supported, a BPDU indicates that
+    /// there is another bridge on the link, which may form a loop. Blocking the port breaks the
+    /// loop without running STP.
+    guard: bool,
+    is_blocked: bool,
+}
+
+struct FdbEntry {
+    port_index: u32,
+    updated_at: Duration,
+}
+
+/// The default time after which the learned addresses are forgotten.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/bridge/br_private.h#L42>.
+const DEFAULT_AGEING_TIME: Duration = Duration::from_secs(300);
+
+/// The maximum number of addresses that can be learned.
+const MAX_FDB_ENTRIES: usize = 1024;
+
+/// The maximum number of frames that are queued for the bridge itself.
+const RX_QUEUE_LEN: usize = 64;
+
+/// The address of STP frames (i.e., BPDUs).
+const STP_GROUP_ADDR: EthernetAddress = EthernetAddress([0x01, 0x80, 0xC2, 0x00, 0x00, 0x00]);
+
+/// The length of the Ethernet header.
+const ETH_HLEN: usize = 14;
+
+impl Bridge {
+    /// Returns the iface of the bridge.
+    pub fn iface(&self) -> Option<Arc<Iface>> {
+        self.iface.get().and_then(Weak::upgrade)
+    }
+
+    /// Returns the time after which the learned addresses are forgotten.
+    pub fn ageing_time(&self) -> Duration {
+        Duration::from_millis(self.ageing_time_ms.load(Ordering::Relaxed))
+    }
+
+    /// Sets the time after which the learned addresses are forgotten.
+    pub fn set_ageing_time(&self, ageing_time: Duration) {
+        self.ageing_time_ms
+            .store(ageing_time.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns whether the port has the BPDU guard enabled.
+    ///
+    /// This method returns `None` if the iface is not a port of the bridge.
+    pub fn port_guard(&self, port_index: u32) -> Option<bool> {
+        self.ports
+            .lock()
+            .iter()
+            .find(|port| port.iface.index() == port_index)
+            .map(|port| port.guard)
+    }
+
+    /// Returns whether the port is blocked by the BPDU guard.
+    ///
+    /// This method returns `None` if the iface is not a port of the bridge.
+    pub fn is_port_blocked(&self, port_index: u32) -> Option<bool> {
+        self.ports
+            .lock()
+            .iter()
+            .find(|port| port.iface.index() == port_index)
+            .map(|port| port.is_blocked)
+    }
+
+    /// Enables or disables the BPDU guard of the port.
+    pub fn set_port_guard(&self, port_index: u32, guard: bool) -> Result<()> {
+        self.with_port_mut(port_index, |port| port.guard = guard)
+    }
+
+    /// Blocks or unblocks the port.
+    ///
+    /// A port that has been blocked by the BPDU guard stays blocked until it is explicitly
+    /// unblocked, so it can forward frames again after the loop is removed.
+    pub fn set_port_blocked(&self, port_index: u32, is_blocked: bool) -> Result<()> {
+        self.with_port_mut(port_index, |port| port.is_blocked = is_blocked)?;
+
+        if is_blocked {
+            self.fdb
+                .lock()
+                .retain(|_, entry| entry.port_index != port_index);
+        }
+
+        Ok(())
+    }
+
+    fn with_port_mut(&self, port_index: u32, f: impl FnOnce(&mut Port)) -> Result<()> {
+        let mut ports = self.ports.lock();
+        let Some(port) = ports
+            .iter_mut()
+            .find(|port| port.iface.index() == port_index)
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the interface is not a bridge port");
+        };
+
+        f(port);
+
+        Ok(())
+    }
+
+    fn add_port(self: &Arc<Self>, iface: Arc<Iface>) -> Result<()> {
+        let Some(ether_addr) = iface.ether_addr() else {
+            return_errno_with_message!(Errno::EINVAL, "only Ethernet interfaces can be enslaved");
+        };
+
+        if !iface.register_rx_handler(self.clone()) {
+            return_errno_with_message!(Errno::EBUSY, "the interface is already enslaved");
+        }
+
+        self.ports.lock().push(Port {
+            iface,
+            ether_addr,
+            guard: false,
+            is_blocked: false,
+        });
+
+        Ok(())
+    }
+
+    fn remove_port(&self, port_index: u32) {
+        let port = {
+            let mut ports = self.ports.lock();
+            let Some(pos) = ports
+                .iter()
+                .position(|port| port.iface.index() == port_index)
+            else {
+                return;
+            };
+            ports.remove(pos)
+        };
+
+        port.iface.unregister_rx_handler();
+
+        self.fdb
+            .lock()
+            .retain(|_, entry| entry.port_index != port_index);
+    }
+
+    fn has_port(&self, port_index: u32) -> bool {
+        self.ports
+            .lock()
+            .iter()
+            .any(|port| port.iface.index() == port_index)
+    }
+}
+
+impl RxHandler for Bridge {
+    fn handle_frame(&self, iface_index: u32, frame: &[u8]) {
+        if frame.len() < ETH_HLEN {
+            return;
+        }
+        let dst_addr = EthernetAddress::from_bytes(&frame[0..6]);
+        let src_addr = EthernetAddress::from_bytes(&frame[6..12]);
+
+        if !self.check_ingress(iface_index, &src_addr, &dst_addr) {
+            return;
+        }
+
+        let now = read_monotonic_time();
+        self.learn(iface_index, src_addr, now);
+
+        if self.is_local_addr(&dst_addr) {
+            self.deliver_local(frame);
+            return;
+        }
+
+        // Frames sent to the reserved link-local addresses must not be forwarded by bridges.
+        // However, BPDUs are forwarded if STP is not running, so that the other bridges can still
+        // detect loops. See <https://elixir.bootlin.com/linux/v6.13/source/net/bridge/br_input.c#L367>.
+        if !is_link_local(&dst_addr) || dst_addr == STP_GROUP_ADDR {
+            self.forward(Some(iface_index), &dst_addr, frame, now);
+        }
+
+        if dst_addr.is_multicast() {
+            self.deliver_local(frame);
+        }
+    }
+}
+
+impl Bridge {
+    /// Checks whether a frame received from the port should be accepted.
+    fn check_ingress(
+        &self,
+        port_index: u32,
+        src_addr: &EthernetAddress,
+        dst_addr: &EthernetAddress,
+    ) -> bool {
+        let mut ports = self.ports.lock();
+
+        let Some(port) = ports
+            .iter_mut()
+            .find(|port| port.iface.index() == port_index)
+        else {
+            return false;
+        };
+        if port.is_blocked {
+            return false;
+        }
+
+        if *dst_addr == STP_GROUP_ADDR && port.guard {
+            warn!(
+                "BPDU received on {} with the BPDU guard enabled, blocking the port",
+                port.iface.name()
+            );
+            port.is_blocked = true;
+            self.fdb
+                .lock()
+                .retain(|_, entry| entry.port_index != port_index);
+            return false;
+        }
+
+        // A frame that comes from our own addresses must have looped back to us.
+        if !src_addr.is_unicast()
+            || *src_addr == self.ether_addr
+            || ports.iter().any(|port| port.ether_addr == *src_addr)
+        {
+            return false;
+        }
+
+        true
+    }
+
+    /// Learns that the address is behind the port.
+    fn learn(&self, port_index: u32, addr: EthernetAddress, now: Duration) {
+        let ageing_time = self.ageing_time();
+        let mut fdb = self.fdb.lock();
+
+        if fdb.len() >= MAX_FDB_ENTRIES && !fdb.contains_key(&addr) {
+            fdb.retain(|_, entry| !entry.is_expired(now, ageing_time));
+            if fdb.len() >= MAX_FDB_ENTRIES {
+                return;
+            }
+        }
+
+        fdb.insert(
+            addr,
+            FdbEntry {
+                port_index,
+                updated_at: now,
+            },
+        );
+    }
+
+    /// Forwards a frame to the ports.
+    ///
+    /// The frame is sent to the port behind which the destination address is learned, or flooded
+    /// to all the ports if the address is unknown. In either case, the frame is never sent back
+    /// to the port that it is received from (i.e., `in_port`).
+    fn forward(
+        &self,
+        in_port: Option<u32>,
+        dst_addr: &EthernetAddress,
+        frame: &[u8],
+        now: Duration,
+    ) {
+        let out_port = if dst_addr.is_unicast() {
+            let ageing_time = self.ageing_time();
+            self.fdb
+                .lock()
+                .get(dst_addr)
+                .filter(|entry| !entry.is_expired(now, ageing_time))
+                .map(|entry| entry.port_index)
+        } else {
+            None
+        };
+
+        // The ifaces are collected before sending the frame, since the ports cannot be locked
+        // while sending frames through them.
+        let out_ifaces: Vec<Arc<Iface>> = self
+            .ports
+            .lock()
+            .iter()
+            .filter(|port| {
+                let index = port.iface.index();
+                !port.is_blocked
+                    && Some(index) != in_port
+                    && out_port.is_none_or(|out_port| out_port == index)
+            })
+            .map(|port| port.iface.clone())
+            .collect();
+
+        for iface in out_ifaces {
+            // Like other devices, the frame is dropped if the device is busy.
+            iface.send_frame(frame);
+        }
+    }
+
+    /// Returns whether the address belongs to the bridge or one of its ports.
+    fn is_local_addr(&self, addr: &EthernetAddress) -> bool {
+        *addr == self.ether_addr
+            || self
+                .ports
+                .lock()
+                .iter()
+                .any(|port| port.ether_addr == *addr)
+    }
+
+    /// Delivers a frame to the bridge itself.
+    fn deliver_local(&self, frame: &[u8]) {
+        {
+            let mut rx_queue = self.rx_queue.lock();
+            if rx_queue.len() >= RX_QUEUE_LEN {
+                return;
+            }
+            rx_queue.push_back(frame.to_vec());
+        }
+
+        if let Some(iface) = self.iface() {
+            iface.poll();
+        }
+    }
+}
+
+impl FdbEntry {
+    fn is_expired(&self, now: Duration, ageing_time: Duration) -> bool {
+        now >= self.updated_at + ageing_time
+    }
+}
+
+/// Returns whether the address is one of the reserved link-local addresses (i.e.,
+/// 01:80:C2:00:00:0X).
+fn is_link_local(addr: &EthernetAddress) -> bool {
+    addr.0[..5] == STP_GROUP_ADDR.0[..5] && addr.0[5] & 0xF0 == 0
+}
+
+/// The device of a bridge.
+///
+/// The frames sent through the device are forwarded to the ports, and the frames received from
+/// the device are those destined for the bridge itself.
+struct BridgeDevice(Arc<Bridge>);
+
+impl Device for BridgeDevice {
+    type RxToken<'a> = BridgeRxToken;
+    type TxToken<'a> = BridgeTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.0.rx_queue.lock().pop_front()?;
+        Some((BridgeRxToken(frame), BridgeTxToken(&self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(BridgeTxToken(&self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        // FIXME: Linux uses the minimum MTU of the ports.
+        caps.max_transmission_unit = 1514;
+        caps
+    }
+}
+
+impl NotifyDevice for BridgeDevice {
+    fn notify_poll_end(&mut self) {}
+}
+
+struct BridgeRxToken(Vec<u8>);
+
+impl RxToken for BridgeRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+struct BridgeTxToken<'a>(&'a Arc<Bridge>);
+
+impl TxToken for BridgeTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0u8; len];
+        let res = f(&mut frame);
+
+        if len >= ETH_HLEN {
+            let dst_addr = EthernetAddress::from_bytes(&frame[0..6]);
+            self.0
+                .forward(None, &dst_addr, &frame, read_monotonic_time());
+        }
+
+        res
+    }
+}
+
+struct BridgeDriver(Arc<Bridge>);
+
+impl WithDevice for BridgeDriver {
+    type Device = BridgeDevice;
+
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Device) -> R,
+    {
+        f(&mut BridgeDevice(self.0.clone()))
+    }
+}
+
+/// The bridges, indexed by the indexes of their ifaces.
+static BRIDGES: Mutex<BTreeMap<u32, Arc<Bridge>>> = Mutex::new(BTreeMap::new());
+
+/// Creates a new bridge with the given name.
+pub fn new_bridge(name: &str) -> Result<Arc<Bridge>> {
+    let mut ether_addr = [0u8; 6];
+    getrandom(&mut ether_addr);
+    // Use a locally administered unicast address.
+    ether_addr[0] = (ether_addr[0] & !0x01) | 0x02;
+
+    let bridge = Arc::new(Bridge {
+        ether_addr: EthernetAddress(ether_addr),
+        iface: Once::new(),
+        ports: SpinLock::new(Vec::new()),
+        fdb: SpinLock::new(BTreeMap::new()),
+        ageing_time_ms: AtomicU64::new(DEFAULT_AGEING_TIME.as_millis() as u64),
+        rx_queue: SpinLock::new(VecDeque::new()),
+    });
+
+    // FIXME: These flags are currently hardcoded.
+    // In the future, we should set appropriate values.
+    let flags = InterfaceFlags::UP
+        | InterfaceFlags::BROADCAST
+        | InterfaceFlags::RUNNING
+        | InterfaceFlags::MULTICAST
+        | InterfaceFlags::LOWER_UP;
+
+    let iface = EtherIface::new(
+        BridgeDriver(bridge.clone()),
+        bridge.ether_addr,
+        None,
+        None,
+        name.to_owned(),
+        PollScheduler::new(),
+        flags,
+    ) as Arc<Iface>;
+    bridge.iface.call_once(|| Arc::downgrade(&iface));
+
+    let mut bridges = BRIDGES.lock();
+    register_iface(iface.clone())?;
+    bridges.insert(iface.index(), bridge.clone());
+
+    Ok(bridge)
+}
+
+/// Deletes the bridge and releases all its ports.
+pub fn delete_bridge(index: u32) -> Result<()> {
+    let Some(bridge) = BRIDGES.lock().remove(&index) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only bridges can be deleted");
+    };
+
+    let ports: Vec<u32> = bridge
+        .ports
+        .lock()
+        .iter()
+        .map(|port| port.iface.index())
+        .collect();
+    for port_index in ports {
+        bridge.remove_port(port_index);
+    }
+
+    if let Some(iface) = bridge.iface() {
+        unregister_iface(&iface);
+    }
+
+    Ok(())
+}
+
+/// Finds the bridge whose iface has the given index.
+pub fn find_bridge(index: u32) -> Option<Arc<Bridge>> {
+    BRIDGES.lock().get(&index).cloned()
+}
+
+/// Finds the bridge that the iface is enslaved to.
+///
+/// This method returns the index of the bridge and the bridge itself.
+pub fn find_master(port_index: u32) -> Option<(u32, Arc<Bridge>)> {
+    BRIDGES
+        .lock()
+        .iter()
+        .find(|(_, bridge)| bridge.has_port(port_index))
+        .map(|(index, bridge)| (*index, bridge.clone()))
+}
+
+/// Enslaves the iface to the bridge with the given index, or releases the iface from its bridge
+/// if the index is zero.
+pub fn set_master(iface: &Arc<Iface>, master_index: u32) -> Result<()> {
+    let old_master = find_master(iface.index());
+    if old_master
+        .as_ref()
+        .is_some_and(|(index, _)| *index == master_index)
+    {
+        return Ok(());
+    }
+
+    let new_master = if master_index == 0 {
+        None
+    } else if let Some(bridge) = find_bridge(master_index) {
+        Some(bridge)
+    } else if iter_all_ifaces().any(|iface| iface.index() == master_index) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the master is not a bridge");
+    } else {
+        return_errno_with_message!(Errno::EINVAL, "the master does not exist");
+    };
+
+    // The checks are from <https://elixir.bootlin.com/linux/v6.13/source/net/bridge/br_if.c#L577>.
+    if new_master.is_some() {
+        if find_bridge(iface.index()).is_some() {
+            return_errno_with_message!(Errno::ELOOP, "bridges cannot be enslaved to bridges");
+        }
+        if iface.type_() != InterfaceType::ETHER || iface.ether_addr().is_none() {
+            return_errno_with_message!(Errno::EINVAL, "only Ethernet interfaces can be enslaved");
+        }
+    }
+
+    if let Some((_, old_master)) = old_master {
+        old_master.remove_port(iface.index());
+    }
+    match new_master {
+        Some(new_master) => new_master.add_port(iface.clone()),
+        None => Ok(()),
+    }
+}
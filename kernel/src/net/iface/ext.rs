@@ -1,7 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::sched::PollScheduler;
-use crate::net::socket::ip::{datagram::DatagramObserver, stream::StreamObserver};
+use crate::net::{
+    netfilter::Netfilter,
+    socket::{
+        ip::{datagram::DatagramObserver, stream::StreamObserver},
+        packet::PacketTap,
+        RealTimePacketClock,
+    },
+};
 
 pub struct BigtcpExt;
 
@@ -10,4 +17,9 @@ impl aster_bigtcp::ext::Ext for BigtcpExt {
 
     type TcpEventObserver = StreamObserver;
     type UdpEventObserver = DatagramObserver;
+    type IcmpEventObserver = DatagramObserver;
+    type PacketClock = RealTimePacketClock;
+
+    type FrameTap = PacketTap;
+    type PacketFilter = Netfilter;
 }
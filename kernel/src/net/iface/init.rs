@@ -1,7 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{borrow::ToOwned, sync::Arc};
-use core::slice::Iter;
 
 use aster_bigtcp::{
     device::WithDevice,
@@ -10,37 +9,92 @@ use aster_bigtcp::{
 use aster_softirq::BottomHalfDisabled;
 use spin::Once;
 
-use super::{poll::poll_ifaces, Iface};
+use super::{
+    poll::{poll_ifaces, spawn_background_poll_thread},
+    Iface,
+};
 use crate::{net::iface::sched::PollScheduler, prelude::*};
 
-static IFACES: Once<Vec<Arc<Iface>>> = Once::new();
+static LOOPBACK_IFACE: Once<Arc<Iface>> = Once::new();
+static VIRTIO_IFACE: Once<Option<Arc<Iface>>> = Once::new();
+
+/// All the ifaces, including those created at runtime (e.g., bridges).
+static IFACES: RwLock<Vec<Arc<Iface>>, BottomHalfDisabled> = RwLock::new(Vec::new());
 
 pub fn loopback_iface() -> &'static Arc<Iface> {
-    &IFACES.get().unwrap()[0]
+    LOOPBACK_IFACE.get().unwrap()
 }
 
 pub fn virtio_iface() -> Option<&'static Arc<Iface>> {
-    IFACES.get().unwrap().get(1)
+    VIRTIO_IFACE.get().unwrap().as_ref()
 }
 
-pub fn iter_all_ifaces() -> Iter<'static, Arc<Iface>> {
-    IFACES.get().unwrap().iter()
+/// Iterates over all the ifaces.
+///
+/// The ifaces are collected before the iteration, so the ifaces that are registered or
+/// unregistered during the iteration do not affect the iteration.
+pub fn iter_all_ifaces() -> vec::IntoIter<Arc<Iface>> {
+    IFACES.read().clone().into_iter()
 }
 
-pub fn init() {
-    IFACES.call_once(|| {
-        let mut ifaces = Vec::with_capacity(2);
+/// Allocates an unused iface name from the template.
+///
+/// Like Linux, the `%d` in the template is replaced with the smallest number that makes the name
+/// unused.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/dev.c#L1110>.
+pub fn alloc_iface_name(template: &str) -> Result<String> {
+    const MAX_UNITS: u32 = 32768;
+    // The maximum length of iface names, excluding the nul terminator.
+    const MAX_NAME_LEN: usize = 15;
+
+    if template.matches("%d").count() != 1 {
+        return_errno_with_message!(Errno::EINVAL, "the name template is invalid");
+    }
 
-        // Initialize loopback before virtio
-        // to ensure the loopback interface index is ahead of virtio.
-        ifaces.push(new_loopback());
+    let ifaces = IFACES.read();
+    (0..MAX_UNITS)
+        .map(|unit| template.replace("%d", &unit.to_string()))
+        .take_while(|name| name.len() <= MAX_NAME_LEN)
+        .find(|name| ifaces.iter().all(|iface| iface.name() != name.as_str()))
+        .ok_or_else(|| Error::with_message(Errno::ENFILE, "no iface name is available"))
+}
 
-        if let Some(iface_virtio) = new_virtio() {
-            ifaces.push(iface_virtio);
-        }
+/// Registers an iface created at runtime and starts polling it in the background.
+///
+/// This method fails if the name of the iface is already used by another iface.
+pub(super) fn register_iface(iface: Arc<Iface>) -> Result<()> {
+    let mut ifaces = IFACES.write();
+    if ifaces.iter().any(|other| other.name() == iface.name()) {
+        return_errno_with_message!(Errno::EEXIST, "the interface name is already used");
+    }
+    ifaces.push(iface.clone());
+    drop(ifaces);
 
-        ifaces
-    });
+    spawn_background_poll_thread(iface);
+
+    Ok(())
+}
+
+/// Unregisters an iface and stops polling it in the background.
+pub(super) fn unregister_iface(iface: &Arc<Iface>) {
+    IFACES.write().retain(|other| !Arc::ptr_eq(other, iface));
+
+    iface.sched_poll().stop();
+}
+
+pub fn init() {
+    // Initialize loopback before virtio
+    // to ensure the loopback interface index is ahead of virtio.
+    let iface_loopback = LOOPBACK_IFACE.call_once(new_loopback);
+    let iface_virtio = VIRTIO_IFACE.call_once(new_virtio);
+
+    let mut ifaces = IFACES.write();
+    ifaces.push(iface_loopback.clone());
+    if let Some(iface_virtio) = iface_virtio {
+        ifaces.push(iface_virtio.clone());
+    }
+    drop(ifaces);
 
     if let Some(iface_virtio) = virtio_iface() {
         for (name, _) in aster_network::all_devices() {
@@ -66,7 +120,12 @@ fn new_virtio() -> Option<Arc<Iface>> {
     const VIRTIO_ADDRESS_PREFIX_LEN: u8 = 24; // mask: 255.255.255.0
     const VIRTIO_GATEWAY: Ipv4Address = Ipv4Address::new(10, 0, 2, 2);
 
-    let virtio_net = aster_network::get_device(DEVICE_NAME)?;
+    // Fall back to the Intel NICs so that the network works on the machines without virtio.
+    let virtio_net = aster_network::get_device(DEVICE_NAME).or_else(|| {
+        aster_e1000e::device_names()
+            .first()
+            .and_then(|name| aster_network::get_device(name))
+    })?;
 
     let ether_addr = virtio_net.lock().mac_addr().0;
 
@@ -95,8 +154,8 @@ fn new_virtio() -> Option<Arc<Iface>> {
     Some(EtherIface::new(
         Wrapper(virtio_net),
         EthernetAddress(ether_addr),
-        Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN),
-        VIRTIO_GATEWAY,
+        Some(Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN)),
+        Some(VIRTIO_GATEWAY),
         "eth0".to_owned(),
         PollScheduler::new(),
         flags,
@@ -107,11 +166,12 @@ fn new_loopback() -> Arc<Iface> {
     use aster_bigtcp::{
         device::{Loopback, Medium},
         iface::IpIface,
-        wire::{Ipv4Address, Ipv4Cidr},
+        wire::{Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
     };
 
     const LOOPBACK_ADDRESS: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
     const LOOPBACK_ADDRESS_PREFIX_LEN: u8 = 8; // mask: 255.0.0.0
+    const LOOPBACK_IPV6_ADDRESS_PREFIX_LEN: u8 = 128;
 
     struct Wrapper(Mutex<Loopback>);
 
@@ -134,12 +194,19 @@ fn new_loopback() -> Arc<Iface> {
         | InterfaceFlags::RUNNING
         | InterfaceFlags::LOWER_UP;
 
-    IpIface::new(
+    let iface = IpIface::new(
         Wrapper(Mutex::new(Loopback::new(Medium::Ip))),
-        Ipv4Cidr::new(LOOPBACK_ADDRESS, LOOPBACK_ADDRESS_PREFIX_LEN),
+        Some(Ipv4Cidr::new(LOOPBACK_ADDRESS, LOOPBACK_ADDRESS_PREFIX_LEN)),
         "lo".to_owned(),
         PollScheduler::new(),
         InterfaceType::LOOPBACK,
         flags,
-    ) as Arc<Iface>
+    ) as Arc<Iface>;
+
+    iface.add_ipv6_addr(Ipv6Cidr::new(
+        Ipv6Address::LOCALHOST,
+        LOOPBACK_IPV6_ADDRESS_PREFIX_LEN,
+    ));
+
+    iface
 }
@@ -1,12 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod bridge;
 mod ext;
 mod init;
 mod poll;
 mod sched;
+mod tun;
+mod wireguard;
 
-pub use init::{init, iter_all_ifaces, loopback_iface, virtio_iface};
+pub use bridge::{delete_bridge, find_bridge, find_master, new_bridge, set_master, Bridge};
+pub use init::{alloc_iface_name, init, iter_all_ifaces, loopback_iface, virtio_iface};
 pub use poll::lazy_init;
+pub use tun::{delete_tun, is_tun, TunFile};
+pub use wireguard::{
+    delete_wireguard, find_wireguard, new_wireguard, WireGuard, WireGuardConfig,
+    WireGuardPeerConfig, WireGuardPeerStatus, WireGuardStatus, WG_KEY_LEN,
+};
 
 pub type Iface = dyn aster_bigtcp::iface::Iface<ext::BigtcpExt>;
 pub type BoundPort = aster_bigtcp::iface::BoundPort<ext::BigtcpExt>;
@@ -16,3 +25,4 @@ pub type RawTcpSocketExt = aster_bigtcp::socket::RawTcpSocketExt<ext::BigtcpExt>
 pub type TcpConnection = aster_bigtcp::socket::TcpConnection<ext::BigtcpExt>;
 pub type TcpListener = aster_bigtcp::socket::TcpListener<ext::BigtcpExt>;
 pub type UdpSocket = aster_bigtcp::socket::UdpSocket<ext::BigtcpExt>;
+pub type IcmpSocket = aster_bigtcp::socket::IcmpSocket<ext::BigtcpExt>;
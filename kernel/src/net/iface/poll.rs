@@ -15,7 +15,7 @@ use crate::{
 
 pub fn lazy_init() {
     for iface in iter_all_ifaces() {
-        spawn_background_poll_thread(iface.clone());
+        spawn_background_poll_thread(iface);
     }
 }
 
@@ -25,7 +25,7 @@ pub(super) fn poll_ifaces() {
     }
 }
 
-fn spawn_background_poll_thread(iface: Arc<Iface>) {
+pub(super) fn spawn_background_poll_thread(iface: Arc<Iface>) {
     let task_fn = move || {
         trace!("spawn background poll thread for {}", iface.name());
 
@@ -33,10 +33,15 @@ fn spawn_background_poll_thread(iface: Arc<Iface>) {
         let wait_queue = sched_poll.polling_wait_queue();
 
         loop {
-            let next_poll_at_ms = if let Some(next_poll_at_ms) = sched_poll.next_poll_at_ms() {
-                next_poll_at_ms
-            } else {
-                wait_queue.wait_until(|| sched_poll.next_poll_at_ms())
+            // The thread exits if the iface is unregistered.
+            let Some(next_poll_at_ms) = wait_queue.wait_until(|| {
+                if sched_poll.is_stopped() {
+                    Some(None)
+                } else {
+                    sched_poll.next_poll_at_ms().map(Some)
+                }
+            }) else {
+                break;
             };
 
             let now_as_ms = Jiffies::elapsed().as_duration().as_millis() as u64;
@@ -56,9 +61,14 @@ fn spawn_background_poll_thread(iface: Arc<Iface>) {
 
             let duration = Duration::from_millis(next_poll_at_ms - now_as_ms);
             let _ = wait_queue.wait_until_or_timeout(
-                // If `sched_poll.next_poll_at_ms()` changes to an earlier time, we will end the
-                // waiting.
-                || (sched_poll.next_poll_at_ms()? < next_poll_at_ms).then_some(()),
+                // If `sched_poll.next_poll_at_ms()` changes to an earlier time or the polling is
+                // stopped, we will end the waiting.
+                || {
+                    if sched_poll.is_stopped() {
+                        return Some(());
+                    }
+                    (sched_poll.next_poll_at_ms()? < next_poll_at_ms).then_some(())
+                },
                 &duration,
             );
         }
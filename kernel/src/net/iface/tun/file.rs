@@ -0,0 +1,489 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::Ordering;
+
+use super::{TunKind, TunQueue, ETH_HLEN};
+use crate::{
+    events::IoEvents,
+    fs::{inode_handle::FileIo, utils::IoctlCmd},
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+    },
+};
+
+/// A file opened from `/dev/net/tun`.
+///
+/// The file is not associated with any device until `TUNSETIFF` is issued. After that, the
+/// file is a queue of the device.
+pub struct TunFile {
+    queue: Arc<TunQueue>,
+}
+
+/// The options of the packets exchanged with user space.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TunOptions {
+    /// The flags that affect the packet format (i.e., `IFF_NO_PI`, `IFF_VNET_HDR`, and
+    /// `IFF_ONE_QUEUE`).
+    flags: TunFlags,
+    /// The length of the virtio network header, which is set by `TUNSETVNETHDRSZ`.
+    vnet_hdr_len: usize,
+}
+
+impl Default for TunOptions {
+    fn default() -> Self {
+        Self {
+            flags: TunFlags::empty(),
+            vnet_hdr_len: size_of::<CVirtioNetHdr>(),
+        }
+    }
+}
+
+bitflags! {
+    /// The flags of TUN/TAP devices in `struct ifreq`.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_tun.h#L65>.
+    struct TunFlags: u16 {
+        const TUN          = 0x0001;
+        const TAP          = 0x0002;
+        const NAPI         = 0x0010;
+        const NAPI_FRAGS   = 0x0020;
+        const NO_CARRIER   = 0x0040;
+        const MULTI_QUEUE  = 0x0100;
+        const ATTACH_QUEUE = 0x0200;
+        const DETACH_QUEUE = 0x0400;
+        const PERSIST      = 0x0800;
+        const NO_PI        = 0x1000;
+        const ONE_QUEUE    = 0x2000;
+        const VNET_HDR     = 0x4000;
+        const TUN_EXCL     = 0x8000;
+    }
+}
+
+bitflags! {
+    /// The offloads that user space can handle.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_tun.h#L84>.
+    struct TunOffloads: u32 {
+        const CSUM    = 0x01;
+        const TSO4    = 0x02;
+        const TSO6    = 0x04;
+        const TSO_ECN = 0x08;
+        const UFO     = 0x10;
+        const USO4    = 0x20;
+        const USO6    = 0x40;
+    }
+}
+
+/// The flags that are kept as the options of the packet format.
+const OPTION_FLAGS: TunFlags = TunFlags::NO_PI
+    .union(TunFlags::VNET_HDR)
+    .union(TunFlags::ONE_QUEUE);
+
+/// The features that are reported by `TUNGETFEATURES`.
+const TUN_FEATURES: TunFlags = TunFlags::TUN
+    .union(TunFlags::TAP)
+    .union(TunFlags::MULTI_QUEUE)
+    .union(OPTION_FLAGS);
+
+/// The length of the name in `struct ifreq` (i.e., `IFNAMSIZ`).
+const IFNAMSIZ: usize = 16;
+
+/// `struct ifreq` with the flags in its union.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if.h#L234>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CIfReq {
+    name: [u8; IFNAMSIZ],
+    flags: u16,
+    _pad: [u8; 22],
+}
+
+/// The packet information that precedes each packet unless `IFF_NO_PI` is set.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_tun.h#L101>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CTunPi {
+    flags: u16,
+    /// The protocol of the packet in network byte order.
+    proto: u16,
+}
+
+/// The flag in [`CTunPi`] that indicates that the packet is truncated.
+const TUN_PKT_STRIP: u16 = 0x0001;
+
+/// The virtio network header that precedes each packet if `IFF_VNET_HDR` is set.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/virtio_net.h#L174>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CVirtioNetHdr {
+    flags: u8,
+    gso_type: u8,
+    hdr_len: u16,
+    gso_size: u16,
+    csum_start: u16,
+    csum_offset: u16,
+}
+
+/// The flag in [`CVirtioNetHdr`] that indicates that the checksum needs to be computed.
+const VIRTIO_NET_HDR_F_NEEDS_CSUM: u8 = 1;
+/// The GSO type in [`CVirtioNetHdr`] that indicates that the packet is not segmented.
+const VIRTIO_NET_HDR_GSO_NONE: u8 = 0;
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+
+/// The maximum length of the packets written by user space.
+const MAX_PACKET_LEN: usize = 65535 + ETH_HLEN;
+
+impl TunFile {
+    pub fn new() -> Self {
+        Self {
+            queue: TunQueue::new(),
+        }
+    }
+
+    fn set_iff(&self, arg: Vaddr) -> Result<i32> {
+        let mut ifreq = current_userspace!().read_val::<CIfReq>(arg)?;
+        check_net_admin()?;
+
+        let flags = TunFlags::from_bits_truncate(ifreq.flags);
+        let kind = match flags & (TunFlags::TUN | TunFlags::TAP) {
+            TunFlags::TUN => TunKind::Tun,
+            TunFlags::TAP => TunKind::Tap,
+            _ => return_errno_with_message!(Errno::EINVAL, "the device type is invalid"),
+        };
+
+        // Like Linux, the last byte of the name is ignored.
+        let name_len = ifreq.name[..IFNAMSIZ - 1]
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(IFNAMSIZ - 1);
+        let name = match core::str::from_utf8(&ifreq.name[..name_len]) {
+            Ok("") => None,
+            Ok(name) => Some(name),
+            Err(_) => return_errno_with_message!(Errno::EINVAL, "the name is not valid UTF-8"),
+        };
+
+        let tun = self.queue.set_iff(
+            name,
+            kind,
+            flags.contains(TunFlags::MULTI_QUEUE),
+            flags.contains(TunFlags::TUN_EXCL),
+        )?;
+        tun.options.lock().flags = flags & OPTION_FLAGS;
+
+        ifreq.name = [0; IFNAMSIZ];
+        ifreq.name[..tun.name.len()].copy_from_slice(tun.name.as_bytes());
+        current_userspace!().write_val(arg, &ifreq)?;
+
+        Ok(0)
+    }
+
+    fn get_iff(&self, arg: Vaddr) -> Result<i32> {
+        let tun = self.queue.tun()?;
+
+        let mut flags = match tun.kind {
+            TunKind::Tun => TunFlags::TUN,
+            TunKind::Tap => TunFlags::TAP,
+        };
+        flags |= tun.options.lock().flags;
+        if tun.is_multi_queue {
+            flags |= TunFlags::MULTI_QUEUE;
+        }
+        if tun.is_persistent.load(Ordering::Relaxed) {
+            flags |= TunFlags::PERSIST;
+        }
+
+        let mut ifreq = CIfReq::new_zeroed();
+        ifreq.name[..tun.name.len()].copy_from_slice(tun.name.as_bytes());
+        ifreq.flags = flags.bits();
+        current_userspace!().write_val(arg, &ifreq)?;
+
+        Ok(0)
+    }
+
+    fn set_queue(&self, arg: Vaddr) -> Result<i32> {
+        let ifreq = current_userspace!().read_val::<CIfReq>(arg)?;
+
+        let flags = TunFlags::from_bits_truncate(ifreq.flags);
+        if flags.contains(TunFlags::ATTACH_QUEUE) {
+            check_net_admin()?;
+            self.queue.reattach()?;
+        } else if flags.contains(TunFlags::DETACH_QUEUE) {
+            self.queue.detach()?;
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "the queue operation is invalid");
+        }
+
+        Ok(0)
+    }
+
+    fn set_offload(&self, arg: usize) -> Result<i32> {
+        self.queue.tun()?;
+
+        // Like Linux, unknown offloads are rejected, so user space can probe the supported
+        // offloads by trying to enable them.
+        let Some(offloads) = TunOffloads::from_bits(arg as u32) else {
+            return_errno_with_message!(Errno::EINVAL, "the offloads are unknown");
+        };
+        // The offloads tell whether user space can handle the packets with partial checksums
+        // or the segmented packets. Since the iface never sends such packets, the checksum
+        // offload has no effect, and the segmentation offloads are not supported.
+        if !(offloads - TunOffloads::CSUM).is_empty() {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the segmentation offloads are not supported"
+            );
+        }
+
+        Ok(0)
+    }
+
+    fn set_vnet_hdr_len(&self, arg: Vaddr) -> Result<i32> {
+        let tun = self.queue.tun()?;
+
+        let vnet_hdr_len = current_userspace!().read_val::<i32>(arg)?;
+        if vnet_hdr_len < size_of::<CVirtioNetHdr>() as i32 {
+            return_errno_with_message!(Errno::EINVAL, "the virtio network header is too short");
+        }
+
+        tun.options.lock().vnet_hdr_len = vnet_hdr_len as usize;
+
+        Ok(0)
+    }
+}
+
+impl Drop for TunFile {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+impl Pollable for TunFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.queue
+            .pollee
+            .poll_with(mask, poller, || self.queue.check_io_events())
+    }
+}
+
+impl FileIo for TunFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let tun = self.queue.tun()?;
+        let options = *tun.options.lock();
+
+        let mut header_len = 0;
+        if !options.flags.contains(TunFlags::NO_PI) {
+            header_len += size_of::<CTunPi>();
+        }
+        if options.flags.contains(TunFlags::VNET_HDR) {
+            header_len += options.vnet_hdr_len;
+        }
+        if writer.avail() < header_len {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for the headers");
+        }
+
+        let packet = self.queue.pop_packet();
+        self.queue.pollee.invalidate();
+        let Some(packet) = packet else {
+            return_errno_with_message!(Errno::EAGAIN, "there are no packets");
+        };
+
+        let packet_len = packet.len().min(writer.avail() - header_len);
+        let mut bytes = Vec::with_capacity(header_len + packet_len);
+
+        if !options.flags.contains(TunFlags::NO_PI) {
+            let proto = match tun.kind {
+                TunKind::Tun => match packet.first().map(|byte| byte >> 4) {
+                    Some(4) => ETH_P_IP,
+                    Some(6) => ETH_P_IPV6,
+                    _ => 0,
+                },
+                TunKind::Tap => packet
+                    .get(12..ETH_HLEN)
+                    .map_or(0, |proto| u16::from_be_bytes([proto[0], proto[1]])),
+            };
+            let pi = CTunPi {
+                flags: if packet_len < packet.len() {
+                    TUN_PKT_STRIP
+                } else {
+                    0
+                },
+                proto: proto.to_be(),
+            };
+            bytes.extend_from_slice(pi.as_bytes());
+        }
+
+        if options.flags.contains(TunFlags::VNET_HDR) {
+            // The packets sent by the iface are neither segmented nor checksummed partially.
+            let vnet_hdr = CVirtioNetHdr {
+                flags: 0,
+                gso_type: VIRTIO_NET_HDR_GSO_NONE,
+                hdr_len: 0,
+                gso_size: 0,
+                csum_start: 0,
+                csum_offset: 0,
+            };
+            bytes.extend_from_slice(vnet_hdr.as_bytes());
+            bytes.resize(
+                bytes.len() + options.vnet_hdr_len - size_of::<CVirtioNetHdr>(),
+                0,
+            );
+        }
+
+        bytes.extend_from_slice(&packet[..packet_len]);
+        writer.write_fallible(&mut bytes.as_slice().into())?;
+
+        Ok(bytes.len())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let tun = self.queue.tun()?;
+        let options = *tun.options.lock();
+
+        let total_len = reader.remain();
+
+        let mut proto = None;
+        if !options.flags.contains(TunFlags::NO_PI) {
+            if reader.remain() < size_of::<CTunPi>() {
+                return_errno_with_message!(Errno::EINVAL, "the packet information is missing");
+            }
+            let pi = reader.read_val::<CTunPi>()?;
+            proto = Some(u16::from_be(pi.proto));
+        }
+
+        let mut vnet_hdr = None;
+        if options.flags.contains(TunFlags::VNET_HDR) {
+            if reader.remain() < options.vnet_hdr_len {
+                return_errno_with_message!(Errno::EINVAL, "the virtio network header is missing");
+            }
+            vnet_hdr = Some(reader.read_val::<CVirtioNetHdr>()?);
+            reader.skip(options.vnet_hdr_len - size_of::<CVirtioNetHdr>());
+        }
+
+        let packet_len = reader.remain();
+        if packet_len > MAX_PACKET_LEN {
+            return_errno_with_message!(Errno::EINVAL, "the packet is too long");
+        }
+        let mut packet = vec![0u8; packet_len];
+        reader.read_fallible(&mut packet.as_mut_slice().into())?;
+
+        match tun.kind {
+            TunKind::Tun => {
+                let version = packet.first().map(|byte| byte >> 4);
+                let is_valid = match proto {
+                    Some(ETH_P_IP) => version == Some(4),
+                    Some(ETH_P_IPV6) => version == Some(6),
+                    Some(_) => false,
+                    None => matches!(version, Some(4) | Some(6)),
+                };
+                if !is_valid {
+                    return_errno_with_message!(Errno::EINVAL, "the packet is not an IP packet");
+                }
+            }
+            TunKind::Tap => {
+                if packet_len < ETH_HLEN {
+                    return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+                }
+            }
+        }
+
+        if let Some(vnet_hdr) = vnet_hdr {
+            if vnet_hdr.gso_type != VIRTIO_NET_HDR_GSO_NONE {
+                return_errno_with_message!(Errno::EINVAL, "segmented packets are not supported");
+            }
+            if vnet_hdr.flags & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0 {
+                complete_checksum(
+                    &mut packet,
+                    vnet_hdr.csum_start as usize,
+                    vnet_hdr.csum_offset as usize,
+                )?;
+            }
+        }
+
+        tun.receive(packet);
+
+        Ok(total_len)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::TUNGETFEATURES => {
+                current_userspace!().write_val(arg, &(TUN_FEATURES.bits() as u32))?;
+                Ok(0)
+            }
+            IoctlCmd::TUNSETIFF => self.set_iff(arg),
+            IoctlCmd::TUNGETIFF => self.get_iff(arg),
+            IoctlCmd::TUNSETQUEUE => self.set_queue(arg),
+            IoctlCmd::TUNSETPERSIST => {
+                let tun = self.queue.tun()?;
+                tun.is_persistent.store(arg != 0, Ordering::Relaxed);
+                Ok(0)
+            }
+            IoctlCmd::TUNSETOFFLOAD => self.set_offload(arg),
+            IoctlCmd::TUNGETVNETHDRSZ => {
+                let tun = self.queue.tun()?;
+                let vnet_hdr_len = tun.options.lock().vnet_hdr_len as i32;
+                current_userspace!().write_val(arg, &vnet_hdr_len)?;
+                Ok(0)
+            }
+            IoctlCmd::TUNSETVNETHDRSZ => self.set_vnet_hdr_len(arg),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+/// Completes the partial checksum of the packet.
+///
+/// The checksum field at `csum_start + csum_offset` already contains the checksum of the pseudo
+/// header, and the checksum of the data from `csum_start` is added to it.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/dev.c#L3335>.
+fn complete_checksum(packet: &mut [u8], csum_start: usize, csum_offset: usize) -> Result<()> {
+    let csum_pos = csum_start + csum_offset;
+    if csum_pos + 2 > packet.len() {
+        return_errno_with_message!(Errno::EINVAL, "the checksum is out of the packet");
+    }
+
+    let mut sum = packet[csum_start..]
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    // Like Linux, a zero checksum is sent as all ones, which is the same in the one's
+    // complement arithmetic.
+    let checksum = match !(sum as u16) {
+        0 => 0xffff,
+        checksum => checksum,
+    };
+    packet[csum_pos..csum_pos + 2].copy_from_slice(&checksum.to_be_bytes());
+
+    Ok(())
+}
+
+/// Checks whether the current thread is allowed to create or attach to devices.
+fn check_net_admin() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+    if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "attaching to TUN/TAP devices requires `CAP_NET_ADMIN`"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,550 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! TUN/TAP devices.
+//!
+//! A TUN/TAP device is an iface whose packets are exchanged with user space through the files
+//! opened from `/dev/net/tun`. The packets sent through the iface can be read from the files,
+//! and the packets written to the files are received by the iface. A TUN device works at the IP
+//! layer, so its packets are IP packets. A TAP device works at the link layer, so its packets
+//! are Ethernet frames.
+//!
+//! Each file attached to a device is a queue of the device. A device created with
+//! `IFF_MULTI_QUEUE` can have multiple queues, among which the outgoing packets are distributed
+//! by their flows.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/tun.c>.
+
+mod file;
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_bigtcp::{
+    device::{Device, DeviceCapabilities, Medium, NotifyDevice, RxToken, TxToken, WithDevice},
+    iface::{EtherIface, InterfaceFlags, InterfaceType, IpIface},
+    time::Instant,
+    wire::EthernetAddress,
+};
+use aster_softirq::BottomHalfDisabled;
+pub use file::TunFile;
+use file::TunOptions;
+use spin::Once;
+
+use super::{
+    alloc_iface_name,
+    init::{register_iface, unregister_iface},
+    iter_all_ifaces,
+    sched::PollScheduler,
+    Iface,
+};
+use crate::{events::IoEvents, prelude::*, process::signal::Pollee, util::random::getrandom};
+
+/// A TUN/TAP device.
+struct Tun {
+    name: String,
+    kind: TunKind,
+    is_multi_queue: bool,
+    iface: Once<Weak<Iface>>,
+    queues: SpinLock<Queues, BottomHalfDisabled>,
+    /// Whether the device is kept after all its queues are closed.
+    is_persistent: AtomicBool,
+    /// The options of the packets exchanged with user space.
+    options: SpinLock<TunOptions>,
+    /// The packets written by user space, which are to be received by the iface.
+    rx_queue: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TunKind {
+    Tun,
+    Tap,
+}
+
+/// The queues of a device.
+///
+/// The detached queues are still bound to the device, but they do not receive packets until
+/// they are attached again.
+struct Queues {
+    attached: Vec<Arc<TunQueue>>,
+    detached: Vec<Arc<TunQueue>>,
+}
+
+/// A queue of a device, which is associated with a file opened from `/dev/net/tun`.
+struct TunQueue {
+    state: Mutex<QueueState>,
+    /// The packets sent through the iface, which are to be read by user space.
+    packets: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+    pollee: Pollee,
+}
+
+enum QueueState {
+    Unbound,
+    Attached(Arc<Tun>),
+    Detached(Arc<Tun>),
+}
+
+/// The maximum number of queues of a device.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_tun.h#L25>.
+const MAX_TAP_QUEUES: usize = 256;
+
+/// The maximum number of packets that are queued for user space in each queue.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_tun.h#L23>.
+const TUN_READQ_SIZE: usize = 500;
+
+/// The maximum number of packets written by user space that are queued for the iface.
+const RX_QUEUE_LEN: usize = 64;
+
+/// The length of the Ethernet header.
+const ETH_HLEN: usize = 14;
+
+/// The default MTU of TUN/TAP devices.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/tun.c#L1002>.
+const DEFAULT_MTU: usize = 1500;
+
+impl Tun {
+    fn iface(&self) -> Option<Arc<Iface>> {
+        self.iface.get().and_then(Weak::upgrade)
+    }
+
+    /// Attaches the queue to the device.
+    fn attach(&self, queue: Arc<TunQueue>) -> Result<()> {
+        let mut queues = self.queues.lock();
+
+        let num_queues = queues.attached.len() + queues.detached.len();
+        if !self.is_multi_queue && num_queues > 0 {
+            return_errno_with_message!(Errno::EBUSY, "the device is already attached");
+        }
+        if num_queues >= MAX_TAP_QUEUES {
+            return_errno_with_message!(Errno::E2BIG, "the device has too many queues");
+        }
+
+        queues.attached.push(queue);
+
+        Ok(())
+    }
+
+    /// Passes a packet sent through the iface to user space.
+    fn transmit(&self, packet: Vec<u8>) {
+        let queue = {
+            let queues = self.queues.lock();
+            let num_queues = queues.attached.len();
+            if num_queues == 0 {
+                return;
+            }
+            let flow_hash = match self.kind {
+                TunKind::Tun => flow_hash(&packet),
+                TunKind::Tap => packet.get(ETH_HLEN..).map_or(0, flow_hash),
+            };
+            queues.attached[flow_hash as usize % num_queues].clone()
+        };
+
+        queue.push_packet(packet);
+    }
+
+    /// Passes a packet written by user space to the iface.
+    fn receive(&self, packet: Vec<u8>) {
+        {
+            let mut rx_queue = self.rx_queue.lock();
+            if rx_queue.len() >= RX_QUEUE_LEN {
+                return;
+            }
+            rx_queue.push_back(packet);
+        }
+
+        if let Some(iface) = self.iface() {
+            iface.poll();
+        }
+    }
+}
+
+impl TunQueue {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(QueueState::Unbound),
+            packets: SpinLock::new(VecDeque::new()),
+            pollee: Pollee::new(),
+        })
+    }
+
+    /// Returns the device that the queue is attached to.
+    fn tun(&self) -> Result<Arc<Tun>> {
+        match &*self.state.lock() {
+            QueueState::Attached(tun) => Ok(tun.clone()),
+            QueueState::Unbound | QueueState::Detached(_) => {
+                return_errno_with_message!(Errno::EBADFD, "the file is not attached to a device")
+            }
+        }
+    }
+
+    /// Attaches the queue to the device with the given name.
+    ///
+    /// If the device does not exist, a new device is created with the name, or with a name
+    /// allocated from the template if the name is absent or is a template.
+    fn set_iff(
+        self: &Arc<Self>,
+        name: Option<&str>,
+        kind: TunKind,
+        is_multi_queue: bool,
+        is_excl: bool,
+    ) -> Result<Arc<Tun>> {
+        let mut tuns = TUNS.lock();
+
+        let mut state = self.state.lock();
+        match &*state {
+            QueueState::Unbound => (),
+            QueueState::Attached(_) => {
+                return_errno_with_message!(Errno::EEXIST, "the file is already attached")
+            }
+            QueueState::Detached(_) => {
+                return_errno_with_message!(Errno::EINVAL, "the file is detached from its device")
+            }
+        }
+
+        let iface = name.and_then(|name| iter_all_ifaces().find(|iface| iface.name() == name));
+        let tun = if let Some(iface) = iface {
+            let Some(tun) = tuns.get(&iface.index()) else {
+                return_errno_with_message!(Errno::EINVAL, "the interface is not a TUN/TAP device");
+            };
+            if is_excl {
+                return_errno_with_message!(Errno::EBUSY, "the device already exists");
+            }
+            if tun.kind != kind {
+                return_errno_with_message!(Errno::EINVAL, "the device type does not match");
+            }
+            if tun.is_multi_queue != is_multi_queue {
+                return_errno_with_message!(Errno::EINVAL, "the queue mode does not match");
+            }
+            tun.clone()
+        } else {
+            let name = match name {
+                Some(name) if !name.contains("%d") => name.to_owned(),
+                Some(template) => alloc_iface_name(template)?,
+                None => alloc_iface_name(match kind {
+                    TunKind::Tun => "tun%d",
+                    TunKind::Tap => "tap%d",
+                })?,
+            };
+            let tun = new_tun(name, kind, is_multi_queue)?;
+            tuns.insert(tun.iface().unwrap().index(), tun.clone());
+            tun
+        };
+
+        tun.attach(self.clone())?;
+        *state = QueueState::Attached(tun.clone());
+
+        Ok(tun)
+    }
+
+    /// Attaches the detached queue to its device again.
+    fn reattach(self: &Arc<Self>) -> Result<()> {
+        let mut state = self.state.lock();
+        let QueueState::Detached(tun) = &*state else {
+            return_errno_with_message!(Errno::EINVAL, "the file is not detached");
+        };
+        let tun = tun.clone();
+
+        let mut queues = tun.queues.lock();
+        queues.detached.retain(|queue| !Arc::ptr_eq(queue, self));
+        queues.attached.push(self.clone());
+        drop(queues);
+
+        *state = QueueState::Attached(tun);
+
+        Ok(())
+    }
+
+    /// Detaches the queue from its device, but keeps it bound to the device.
+    fn detach(self: &Arc<Self>) -> Result<()> {
+        let mut state = self.state.lock();
+        let QueueState::Attached(tun) = &*state else {
+            return_errno_with_message!(Errno::EINVAL, "the file is not attached");
+        };
+        if !tun.is_multi_queue {
+            return_errno_with_message!(Errno::EINVAL, "the device has only one queue");
+        }
+        let tun = tun.clone();
+
+        let mut queues = tun.queues.lock();
+        queues.attached.retain(|queue| !Arc::ptr_eq(queue, self));
+        queues.detached.push(self.clone());
+        drop(queues);
+
+        *state = QueueState::Detached(tun);
+        drop(state);
+
+        self.packets.lock().clear();
+        self.pollee.notify(IoEvents::ERR);
+
+        Ok(())
+    }
+
+    /// Unbinds the queue from its device.
+    ///
+    /// The device is deleted if this is its last queue and it is not persistent.
+    fn close(self: &Arc<Self>) {
+        let mut tuns = TUNS.lock();
+
+        let tun = match core::mem::replace(&mut *self.state.lock(), QueueState::Unbound) {
+            QueueState::Unbound => return,
+            QueueState::Attached(tun) | QueueState::Detached(tun) => tun,
+        };
+
+        let mut queues = tun.queues.lock();
+        queues.attached.retain(|queue| !Arc::ptr_eq(queue, self));
+        queues.detached.retain(|queue| !Arc::ptr_eq(queue, self));
+        let is_last_queue = queues.attached.is_empty() && queues.detached.is_empty();
+        drop(queues);
+
+        if !is_last_queue || tun.is_persistent.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some(iface) = tun.iface() {
+            tuns.remove(&iface.index());
+            unregister_iface(&iface);
+        }
+    }
+
+    fn push_packet(&self, packet: Vec<u8>) {
+        {
+            let mut packets = self.packets.lock();
+            if packets.len() >= TUN_READQ_SIZE {
+                return;
+            }
+            packets.push_back(packet);
+        }
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    fn pop_packet(&self) -> Option<Vec<u8>> {
+        self.packets.lock().pop_front()
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if !matches!(*self.state.lock(), QueueState::Attached(_)) {
+            return IoEvents::ERR;
+        }
+
+        if self.packets.lock().is_empty() {
+            IoEvents::OUT
+        } else {
+            IoEvents::IN | IoEvents::OUT
+        }
+    }
+}
+
+/// Computes the hash of the flow that the IP packet belongs to.
+///
+/// The hash is computed from the addresses and, for TCP and UDP packets, the ports, so that the
+/// packets of the same flow always go to the same queue.
+fn flow_hash(packet: &[u8]) -> u32 {
+    // The FNV-1a hash. See <http://www.isthe.com/chongo/tech/comp/fnv/index.html>.
+    const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+    const FNV_PRIME: u32 = 0x01000193;
+
+    const IPPROTO_TCP: u8 = 6;
+    const IPPROTO_UDP: u8 = 17;
+
+    let (addrs, protocol, header_len) = match packet.first().map(|byte| byte >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            (&packet[12..20], packet[9], (packet[0] & 0x0F) as usize * 4)
+        }
+        Some(6) if packet.len() >= 40 => (&packet[8..40], packet[6], 40),
+        _ => return 0,
+    };
+
+    let ports = match protocol {
+        IPPROTO_TCP | IPPROTO_UDP => packet.get(header_len..header_len + 4).unwrap_or(&[]),
+        _ => &[],
+    };
+
+    addrs
+        .iter()
+        .chain(ports.iter())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// The device of a TUN/TAP device.
+///
+/// The packets sent through the device are passed to user space, and the packets received from
+/// the device are those written by user space.
+struct TunDevice(Arc<Tun>);
+
+impl Device for TunDevice {
+    type RxToken<'a> = TunRxToken;
+    type TxToken<'a> = TunTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let packet = self.0.rx_queue.lock().pop_front()?;
+        Some((TunRxToken(packet), TunTxToken(&self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TunTxToken(&self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        match self.0.kind {
+            TunKind::Tun => {
+                caps.medium = Medium::Ip;
+                caps.max_transmission_unit = DEFAULT_MTU;
+            }
+            TunKind::Tap => {
+                caps.medium = Medium::Ethernet;
+                caps.max_transmission_unit = DEFAULT_MTU + ETH_HLEN;
+            }
+        }
+        caps
+    }
+}
+
+impl NotifyDevice for TunDevice {
+    fn notify_poll_end(&mut self) {}
+}
+
+struct TunRxToken(Vec<u8>);
+
+impl RxToken for TunRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+struct TunTxToken<'a>(&'a Arc<Tun>);
+
+impl TxToken for TunTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut packet = vec![0u8; len];
+        let res = f(&mut packet);
+        self.0.transmit(packet);
+        res
+    }
+}
+
+struct TunDriver(Arc<Tun>);
+
+impl WithDevice for TunDriver {
+    type Device = TunDevice;
+
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Device) -> R,
+    {
+        f(&mut TunDevice(self.0.clone()))
+    }
+}
+
+/// The TUN/TAP devices, indexed by the indexes of their ifaces.
+static TUNS: Mutex<BTreeMap<u32, Arc<Tun>>> = Mutex::new(BTreeMap::new());
+
+fn new_tun(name: String, kind: TunKind, is_multi_queue: bool) -> Result<Arc<Tun>> {
+    let tun = Arc::new(Tun {
+        name: name.clone(),
+        kind,
+        is_multi_queue,
+        iface: Once::new(),
+        queues: SpinLock::new(Queues {
+            attached: Vec::new(),
+            detached: Vec::new(),
+        }),
+        is_persistent: AtomicBool::new(false),
+        options: SpinLock::new(TunOptions::default()),
+        rx_queue: SpinLock::new(VecDeque::new()),
+    });
+
+    // FIXME: These flags are currently hardcoded.
+    // In the future, we should set appropriate values.
+    let iface = match kind {
+        TunKind::Tun => {
+            let flags = InterfaceFlags::UP
+                | InterfaceFlags::POINTOPOINT
+                | InterfaceFlags::RUNNING
+                | InterfaceFlags::NOARP
+                | InterfaceFlags::MULTICAST
+                | InterfaceFlags::LOWER_UP;
+
+            IpIface::new(
+                TunDriver(tun.clone()),
+                None,
+                name,
+                PollScheduler::new(),
+                InterfaceType::NONE,
+                flags,
+            ) as Arc<Iface>
+        }
+        TunKind::Tap => {
+            let mut ether_addr = [0u8; 6];
+            getrandom(&mut ether_addr);
+            // Use a locally administered unicast address.
+            ether_addr[0] = (ether_addr[0] & !0x01) | 0x02;
+
+            let flags = InterfaceFlags::UP
+                | InterfaceFlags::BROADCAST
+                | InterfaceFlags::RUNNING
+                | InterfaceFlags::MULTICAST
+                | InterfaceFlags::LOWER_UP;
+
+            EtherIface::new(
+                TunDriver(tun.clone()),
+                EthernetAddress(ether_addr),
+                None,
+                None,
+                name,
+                PollScheduler::new(),
+                flags,
+            ) as Arc<Iface>
+        }
+    };
+    tun.iface.call_once(|| Arc::downgrade(&iface));
+
+    register_iface(iface)?;
+
+    Ok(tun)
+}
+
+/// Deletes the TUN/TAP device.
+///
+/// The files attached to the device are detached, and they can no longer be used to exchange
+/// packets.
+pub fn delete_tun(index: u32) -> Result<()> {
+    let mut tuns = TUNS.lock();
+    let Some(tun) = tuns.remove(&index) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the interface is not a TUN/TAP device");
+    };
+
+    let queues = {
+        let mut queues = tun.queues.lock();
+        let mut all_queues = core::mem::take(&mut queues.attached);
+        all_queues.append(&mut queues.detached);
+        all_queues
+    };
+    for queue in queues {
+        *queue.state.lock() = QueueState::Unbound;
+        queue.packets.lock().clear();
+        queue.pollee.notify(IoEvents::ERR);
+    }
+
+    if let Some(iface) = tun.iface() {
+        unregister_iface(&iface);
+    }
+
+    Ok(())
+}
+
+/// Returns whether the iface with the given index is a TUN/TAP device.
+pub fn is_tun(index: u32) -> bool {
+    TUNS.lock().contains_key(&index)
+}
@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
+use super::noise::Key;
+use crate::prelude::*;
+
+/// The allowed IPs of the peers.
+///
+/// The allowed IPs work as a routing table for the packets sent through the device, where the
+/// peer whose allowed IPs contain the destination address with the longest prefix is chosen. They
+/// also work as an access control list for the packets received from the peers, where the source
+/// address must be one of the allowed IPs of the peer.
+///
+/// Linux uses a trie to store the allowed IPs. We keep them in a list sorted by the prefix
+/// lengths instead, which is sufficient for the small number of peers that a device usually has.
+pub(super) struct AllowedIps {
+    /// The subnets and the public keys of their peers, in descending order of the prefix lengths.
+    entries: Vec<(IpCidr, Key)>,
+}
+
+impl AllowedIps {
+    pub(super) const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Inserts the subnet for the peer.
+    ///
+    /// Like Linux, if the subnet is already allowed for another peer, it is moved to the peer.
+    pub(super) fn insert(&mut self, cidr: IpCidr, peer: &Key) {
+        let cidr = network_of(&cidr);
+
+        if let Some((_, owner)) = self.entries.iter_mut().find(|(other, _)| *other == cidr) {
+            *owner = *peer;
+            return;
+        }
+
+        let pos = self
+            .entries
+            .partition_point(|(other, _)| other.prefix_len() >= cidr.prefix_len());
+        self.entries.insert(pos, (cidr, *peer));
+    }
+
+    /// Removes all the subnets of the peer.
+    pub(super) fn remove_peer(&mut self, peer: &Key) {
+        self.entries.retain(|(_, owner)| owner != peer);
+    }
+
+    /// Finds the peer whose allowed IPs contain the address with the longest prefix.
+    pub(super) fn lookup(&self, addr: &IpAddress) -> Option<&Key> {
+        self.entries
+            .iter()
+            .find(|(cidr, _)| cidr.contains_addr(addr))
+            .map(|(_, peer)| peer)
+    }
+
+    /// Iterates over the subnets of the peer.
+    pub(super) fn iter_peer<'a>(&'a self, peer: &'a Key) -> impl Iterator<Item = IpCidr> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(_, owner)| owner == peer)
+            .map(|(cidr, _)| *cidr)
+    }
+}
+
+/// Clears the host bits of the subnet, as Linux does when the allowed IPs are configured.
+fn network_of(cidr: &IpCidr) -> IpCidr {
+    let prefix_len = cidr.prefix_len();
+
+    let network = match cidr.address() {
+        IpAddress::Ipv4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv4(Ipv4Address::from(u32::from(addr) & mask))
+        }
+        IpAddress::Ipv6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv6(Ipv6Address::from(u128::from(addr) & mask))
+        }
+    };
+
+    IpCidr::new(network, prefix_len)
+}
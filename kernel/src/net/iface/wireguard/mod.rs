@@ -0,0 +1,780 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! WireGuard devices.
+//!
+//! A WireGuard device is an iface that tunnels IP packets to its peers in encrypted UDP
+//! messages. The packets sent through the iface are routed to the peers by their allowed IPs,
+//! encrypted, and sent to the endpoints of the peers. The messages received from the peers are
+//! decrypted, and the packets in them are received by the iface if their source addresses are
+//! allowed IPs of the peers.
+//!
+//! Each device has a kernel thread, which performs the handshakes, encrypts and decrypts the
+//! packets, and handles the timers of the peers.
+//!
+//! Reference: <https://www.wireguard.com/papers/wireguard.pdf>,
+//! <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/wireguard>.
+
+mod allowed_ips;
+mod noise;
+mod peer;
+
+use alloc::collections::btree_map::Entry;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_bigtcp::{
+    device::{Device, DeviceCapabilities, Medium, NotifyDevice, RxToken, TxToken, WithDevice},
+    iface::{BindPortConfig, InterfaceFlags, InterfaceType, IpIface},
+    time::Instant,
+    wire::{IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv6Address},
+};
+use aster_softirq::BottomHalfDisabled;
+use aster_time::read_monotonic_time;
+use ostd::sync::WaitQueue;
+use spin::Once;
+
+use self::{
+    allowed_ips::AllowedIps,
+    noise::{
+        Identity, Key, INITIATION_LEN, KEY_LEN, MESSAGE_COOKIE_REPLY, MESSAGE_INITIATION,
+        MESSAGE_RESPONSE, MESSAGE_TRANSPORT, RESPONSE_LEN,
+    },
+    peer::{IndexTable, Peer, PeerContext, TRANSPORT_HEADER_LEN},
+};
+use super::{
+    init::{register_iface, unregister_iface},
+    loopback_iface,
+    sched::PollScheduler,
+    virtio_iface, Iface, UdpSocket,
+};
+use crate::{
+    events::{IoEvents, Observer},
+    net::socket::ip::datagram::DatagramObserver,
+    prelude::*,
+    process::signal::{PollAdaptor, Pollee},
+    thread::kernel_thread::ThreadOptions,
+    util::crypto::chacha20poly1305::TAG_SIZE,
+};
+
+/// The length of the keys of WireGuard in bytes.
+pub const WG_KEY_LEN: usize = KEY_LEN;
+
+/// A WireGuard device.
+pub struct WireGuard {
+    iface: Once<Weak<Iface>>,
+    state: Mutex<DeviceState>,
+    /// The packets sent through the iface, which are to be encrypted and sent to the peers.
+    tx_queue: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+    /// The packets decrypted from the messages of the peers, which are to be received by the
+    /// iface.
+    rx_queue: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+    /// The pollee that is notified when the UDP sockets receive messages.
+    pollee: Pollee,
+    /// The wait queue of the kernel thread of the device.
+    wait_queue: WaitQueue,
+    /// Whether the kernel thread has work to do.
+    has_work: AtomicBool,
+    is_deleted: AtomicBool,
+}
+
+struct DeviceState {
+    identity: Option<Identity>,
+    listen_port: u16,
+    fwmark: u32,
+    /// The UDP sockets that exchange messages with the peers.
+    sockets: Vec<UdpSocket>,
+    peers: BTreeMap<Key, Peer>,
+    allowed_ips: AllowedIps,
+    indexes: IndexTable,
+}
+
+/// The configuration of a WireGuard device, which is applied by [`WireGuard::configure`].
+///
+/// The settings that are absent are left unchanged.
+#[derive(Debug, Default)]
+pub struct WireGuardConfig {
+    /// The private key of the device, or zeros to remove the private key.
+    pub private_key: Option<[u8; WG_KEY_LEN]>,
+    /// The UDP port of the device, or zero to choose a random port.
+    pub listen_port: Option<u16>,
+    pub fwmark: Option<u32>,
+    /// Whether to remove all the existing peers before configuring the peers.
+    pub replace_peers: bool,
+    pub peers: Vec<WireGuardPeerConfig>,
+}
+
+/// The configuration of a peer of a WireGuard device.
+#[derive(Debug)]
+pub struct WireGuardPeerConfig {
+    pub public_key: [u8; WG_KEY_LEN],
+    /// Whether to remove the peer.
+    pub remove: bool,
+    /// Whether to configure the peer only if it exists, instead of creating it.
+    pub update_only: bool,
+    /// The pre-shared key, or zeros to disable the pre-shared key.
+    pub preshared_key: Option<[u8; WG_KEY_LEN]>,
+    pub endpoint: Option<IpEndpoint>,
+    /// The interval of the persistent keepalives in seconds, or zero to disable them.
+    pub persistent_keepalive_interval: Option<u16>,
+    /// Whether to remove all the existing allowed IPs before adding the allowed IPs.
+    pub replace_allowed_ips: bool,
+    pub allowed_ips: Vec<IpCidr>,
+}
+
+/// The status of a WireGuard device, which is reported by [`WireGuard::status`].
+pub struct WireGuardStatus {
+    pub private_key: Option<[u8; WG_KEY_LEN]>,
+    pub public_key: Option<[u8; WG_KEY_LEN]>,
+    pub listen_port: u16,
+    pub fwmark: u32,
+    pub peers: Vec<WireGuardPeerStatus>,
+}
+
+/// The status of a peer of a WireGuard device.
+pub struct WireGuardPeerStatus {
+    pub public_key: [u8; WG_KEY_LEN],
+    pub preshared_key: [u8; WG_KEY_LEN],
+    pub endpoint: Option<IpEndpoint>,
+    pub persistent_keepalive_interval: u16,
+    /// The wall-clock time of the last handshake, or `None` if there is no handshake yet.
+    pub last_handshake_time: Option<Duration>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub allowed_ips: Vec<IpCidr>,
+}
+
+/// The MTU of WireGuard devices.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/wireguard/device.c#L283>.
+const MTU: usize = 1420;
+
+/// The maximum number of packets queued in each direction.
+const MAX_QUEUED_PACKETS: usize = 1024;
+
+/// The interval at which the kernel thread handles the timers of the peers.
+const TIMER_INTERVAL: Duration = Duration::from_millis(250);
+
+impl WireGuard {
+    pub fn iface(&self) -> Option<Arc<Iface>> {
+        self.iface.get().and_then(Weak::upgrade)
+    }
+
+    /// Returns the status of the device and its peers.
+    pub fn status(&self) -> WireGuardStatus {
+        let state = self.state.lock();
+
+        let peers = state
+            .peers
+            .values()
+            .map(|peer| WireGuardPeerStatus {
+                public_key: *peer.public_key(),
+                preshared_key: peer.preshared_key,
+                endpoint: peer.endpoint,
+                persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                last_handshake_time: peer.last_handshake_time,
+                rx_bytes: peer.rx_bytes,
+                tx_bytes: peer.tx_bytes,
+                allowed_ips: state.allowed_ips.iter_peer(peer.public_key()).collect(),
+            })
+            .collect();
+
+        WireGuardStatus {
+            private_key: state
+                .identity
+                .as_ref()
+                .map(|identity| *identity.private_key()),
+            public_key: state
+                .identity
+                .as_ref()
+                .map(|identity| *identity.public_key()),
+            listen_port: state.listen_port,
+            fwmark: state.fwmark,
+            peers,
+        }
+    }
+
+    /// Applies the configuration to the device.
+    pub fn configure(&self, config: WireGuardConfig) -> Result<()> {
+        let mut state = self.state.lock();
+
+        if let Some(listen_port) = config.listen_port {
+            if listen_port == 0 || listen_port != state.listen_port {
+                let (sockets, listen_port) = self.open_sockets(listen_port)?;
+                state.sockets = sockets;
+                state.listen_port = listen_port;
+            }
+        }
+        if let Some(fwmark) = config.fwmark {
+            // FIXME: The firewall mark is reported but not applied to the sent messages.
+            state.fwmark = fwmark;
+        }
+        if let Some(private_key) = config.private_key {
+            state.set_private_key(private_key);
+        }
+        if config.replace_peers {
+            let public_keys: Vec<Key> = state.peers.keys().copied().collect();
+            for public_key in public_keys {
+                state.remove_peer(&public_key);
+            }
+        }
+        for peer_config in config.peers {
+            state.configure_peer(peer_config);
+        }
+
+        drop(state);
+        self.wake_worker();
+
+        Ok(())
+    }
+
+    /// Opens the UDP sockets that listen on the port.
+    ///
+    /// A random port is chosen if the port is zero. Returns the sockets and the port.
+    //
+    // FIXME: Since sockets cannot be bound to wildcard addresses, we bind one socket to the
+    // IPv4 address of each iface that can reach the peers. IPv6 endpoints are not supported yet.
+    fn open_sockets(&self, mut port: u16) -> Result<(Vec<UdpSocket>, u16)> {
+        let mut sockets = Vec::new();
+
+        for iface in core::iter::once(loopback_iface()).chain(virtio_iface()) {
+            let Some(addr) = iface.ipv4_addr() else {
+                continue;
+            };
+
+            let bound_port = iface.bind(IpAddress::Ipv4(addr), BindPortConfig::new(port, false))?;
+            port = bound_port.port();
+
+            let socket =
+                match UdpSocket::new_bind(bound_port, DatagramObserver::new(self.pollee.clone())) {
+                    Ok(socket) => socket,
+                    Err((_, err)) => {
+                        unreachable!("`new_bind` fails with {:?}, which should not happen", err)
+                    }
+                };
+            sockets.push(socket);
+        }
+
+        Ok((sockets, port))
+    }
+
+    fn wake_worker(&self) {
+        self.has_work.store(true, Ordering::Release);
+        self.wait_queue.wake_all();
+    }
+
+    /// Queues a packet sent through the iface, which will be encrypted by the kernel thread.
+    fn transmit(&self, packet: Vec<u8>) {
+        {
+            let mut tx_queue = self.tx_queue.lock();
+            if tx_queue.len() >= MAX_QUEUED_PACKETS {
+                return;
+            }
+            tx_queue.push_back(packet);
+        }
+
+        self.wake_worker();
+    }
+
+    /// Runs the kernel thread of the device until the device is deleted.
+    fn run(self: Arc<Self>) {
+        let mut poll_adaptor = PollAdaptor::with_observer(WorkerWaker(Arc::downgrade(&self)));
+        self.pollee
+            .register_poller(poll_adaptor.as_handle_mut(), IoEvents::IN);
+
+        while !self.is_deleted.load(Ordering::Acquire) {
+            self.process();
+
+            let _ = self.wait_queue.wait_until_or_timeout(
+                || self.has_work.swap(false, Ordering::AcqRel).then_some(()),
+                &TIMER_INTERVAL,
+            );
+        }
+    }
+
+    fn process(&self) {
+        let now = read_monotonic_time();
+        let mut state = self.state.lock();
+
+        let mut datagrams = Vec::new();
+        for socket in state.sockets.iter() {
+            while let Ok(datagram) = socket.recv(|data, meta, _| (data.to_vec(), meta.endpoint)) {
+                datagrams.push(datagram);
+            }
+        }
+
+        let mut has_received = false;
+        for (mut message, src) in datagrams {
+            let Some(packet) = state.handle_message(&mut message, src, now) else {
+                continue;
+            };
+
+            let mut rx_queue = self.rx_queue.lock();
+            if rx_queue.len() < MAX_QUEUED_PACKETS {
+                rx_queue.push_back(packet);
+                has_received = true;
+            }
+        }
+
+        loop {
+            let Some(packet) = self.tx_queue.lock().pop_front() else {
+                break;
+            };
+            state.handle_packet(packet, now);
+        }
+
+        state.tick(now);
+        drop(state);
+
+        if has_received {
+            if let Some(iface) = self.iface() {
+                iface.poll();
+            }
+        }
+    }
+}
+
+impl DeviceState {
+    fn set_private_key(&mut self, private_key: Key) {
+        if private_key == [0; KEY_LEN] {
+            self.identity = None;
+        } else {
+            let identity = Identity::new(private_key);
+            if self
+                .identity
+                .as_ref()
+                .is_some_and(|old| old.public_key() == identity.public_key())
+            {
+                return;
+            }
+            // Like Linux, the peer that has the same public key as the device is removed.
+            self.remove_peer(identity.public_key());
+            self.identity = Some(identity);
+        }
+
+        // The sessions negotiated with the old identity are no longer valid.
+        for peer in self.peers.values_mut() {
+            peer.clear_sessions(&mut self.indexes);
+        }
+    }
+
+    fn configure_peer(&mut self, config: WireGuardPeerConfig) {
+        let public_key = config.public_key;
+
+        if config.remove {
+            self.remove_peer(&public_key);
+            return;
+        }
+        // Like Linux, the peer that has the same public key as the device is ignored.
+        if self
+            .identity
+            .as_ref()
+            .is_some_and(|identity| identity.public_key() == &public_key)
+        {
+            return;
+        }
+
+        let peer = match self.peers.entry(public_key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(_) if config.update_only => return,
+            Entry::Vacant(entry) => entry.insert(Peer::new(public_key)),
+        };
+        if let Some(preshared_key) = config.preshared_key {
+            peer.preshared_key = preshared_key;
+        }
+        if let Some(endpoint) = config.endpoint {
+            peer.endpoint = Some(endpoint);
+        }
+        if let Some(interval) = config.persistent_keepalive_interval {
+            peer.persistent_keepalive_interval = interval;
+        }
+
+        if config.replace_allowed_ips {
+            self.allowed_ips.remove_peer(&public_key);
+        }
+        for cidr in config.allowed_ips {
+            self.allowed_ips.insert(cidr, &public_key);
+        }
+    }
+
+    fn remove_peer(&mut self, public_key: &Key) {
+        if let Some(mut peer) = self.peers.remove(public_key) {
+            peer.clear_sessions(&mut self.indexes);
+            self.allowed_ips.remove_peer(public_key);
+        }
+    }
+
+    /// Handles a message received from the UDP sockets.
+    ///
+    /// Returns the decrypted packet if the message is a transport message that carries a packet.
+    fn handle_message(
+        &mut self,
+        message: &mut [u8],
+        src: IpEndpoint,
+        now: Duration,
+    ) -> Option<Vec<u8>> {
+        let identity = self.identity.as_ref()?;
+
+        // The message type is a 32-bit integer in little endian.
+        if message.len() < 4 || message[1..4] != [0; 3] {
+            return None;
+        }
+        let message_len = message.len();
+
+        match message[0] {
+            MESSAGE_INITIATION if message_len == INITIATION_LEN => {
+                if !identity.verify_mac1(message) {
+                    return None;
+                }
+                let initiation =
+                    noise::consume_initiation(identity, (&*message).try_into().unwrap())?;
+                let public_key = initiation.peer_public_key;
+                self.with_peer(&public_key, now, |peer, cx| {
+                    if peer.consume_initiation(initiation, cx) {
+                        peer.endpoint = Some(src);
+                        peer.rx_bytes += message_len as u64;
+                    }
+                });
+                None
+            }
+            MESSAGE_RESPONSE if message_len == RESPONSE_LEN => {
+                if !identity.verify_mac1(message) {
+                    return None;
+                }
+                let receiver_index = u32::from_le_bytes(message[8..12].try_into().unwrap());
+                let public_key = *self.indexes.get(receiver_index)?;
+                self.with_peer(&public_key, now, |peer, cx| {
+                    if peer.consume_response((&*message).try_into().unwrap(), cx) {
+                        peer.endpoint = Some(src);
+                        peer.rx_bytes += message_len as u64;
+                    }
+                });
+                None
+            }
+            MESSAGE_COOKIE_REPLY => {
+                // The device never sends `mac2`, so it cannot use the cookies.
+                debug!("the WireGuard cookie reply from {} is ignored", src);
+                None
+            }
+            MESSAGE_TRANSPORT if message_len >= TRANSPORT_HEADER_LEN + TAG_SIZE => {
+                let receiver_index = u32::from_le_bytes(message[4..8].try_into().unwrap());
+                let public_key = *self.indexes.get(receiver_index)?;
+                let mut packet = self
+                    .with_peer(&public_key, now, |peer, cx| {
+                        let packet = peer.consume_transport(message, cx)?;
+                        peer.endpoint = Some(src);
+                        peer.rx_bytes += message_len as u64;
+                        Some(packet)
+                    })
+                    .flatten()?;
+
+                // An empty packet is a keepalive.
+                if packet.is_empty() {
+                    return None;
+                }
+
+                // Remove the padding and check that the peer is allowed to send the packet.
+                let (src_addr, _, len) = parse_ip_header(&packet)?;
+                if self.allowed_ips.lookup(&src_addr) != Some(&public_key) {
+                    return None;
+                }
+                packet.truncate(len);
+
+                Some(packet)
+            }
+            _ => None,
+        }
+    }
+
+    /// Sends a packet to the peer whose allowed IPs contain the destination address.
+    fn handle_packet(&mut self, packet: Vec<u8>, now: Duration) {
+        let Some((_, dst_addr, _)) = parse_ip_header(&packet) else {
+            return;
+        };
+        // FIXME: Linux replies with an ICMP error if there is no such peer.
+        let Some(public_key) = self.allowed_ips.lookup(&dst_addr).copied() else {
+            return;
+        };
+
+        self.with_peer(&public_key, now, |peer, cx| peer.send_packet(packet, cx));
+    }
+
+    fn tick(&mut self, now: Duration) {
+        let public_keys: Vec<Key> = self.peers.keys().copied().collect();
+        for public_key in public_keys {
+            self.with_peer(&public_key, now, |peer, cx| peer.tick(cx));
+        }
+    }
+
+    /// Calls `f` with the peer and sends the messages that it produces to the peer.
+    ///
+    /// Returns `None` if the peer does not exist or the device has no private key.
+    fn with_peer<F, R>(&mut self, public_key: &Key, now: Duration, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Peer, &mut PeerContext) -> R,
+    {
+        let identity = self.identity.as_ref()?;
+        let peer = self.peers.get_mut(public_key)?;
+
+        let mut cx = PeerContext {
+            identity,
+            indexes: &mut self.indexes,
+            now,
+            messages: Vec::new(),
+        };
+        let result = f(peer, &mut cx);
+
+        send_to_peer(&self.sockets, peer, cx.messages);
+
+        Some(result)
+    }
+}
+
+/// Sends the messages to the endpoint of the peer.
+fn send_to_peer(sockets: &[UdpSocket], peer: &mut Peer, messages: Vec<Vec<u8>>) {
+    if messages.is_empty() {
+        return;
+    }
+    let Some(endpoint) = peer.endpoint else {
+        return;
+    };
+    let IpAddress::Ipv4(addr) = endpoint.addr else {
+        return;
+    };
+
+    // Use the socket on the iface whose subnet contains the endpoint, or the last socket, which
+    // is on the default iface.
+    let Some(socket) = sockets
+        .iter()
+        .find(|socket| {
+            socket
+                .iface()
+                .ipv4_addrs()
+                .iter()
+                .any(|cidr| cidr.contains_addr(&addr))
+        })
+        .or(sockets.last())
+    else {
+        return;
+    };
+
+    for message in messages {
+        match socket.send(message.len(), endpoint, |buffer| {
+            buffer.copy_from_slice(&message)
+        }) {
+            Ok(()) => peer.tx_bytes += message.len() as u64,
+            Err(err) => debug!("failed to send the WireGuard message: {:?}", err),
+        }
+    }
+
+    socket.iface().poll();
+}
+
+/// Parses the header of the IP packet.
+///
+/// Returns the source address, the destination address, and the length of the packet, which
+/// may be shorter than the buffer if there is padding.
+fn parse_ip_header(packet: &[u8]) -> Option<(IpAddress, IpAddress, usize)> {
+    const IPV4_HEADER_LEN: usize = 20;
+    const IPV6_HEADER_LEN: usize = 40;
+
+    let (src_addr, dst_addr, len) = match packet.first()? >> 4 {
+        4 if packet.len() >= IPV4_HEADER_LEN => {
+            let src_addr = u32::from_be_bytes(packet[12..16].try_into().unwrap());
+            let dst_addr = u32::from_be_bytes(packet[16..20].try_into().unwrap());
+            let len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+            if len < IPV4_HEADER_LEN {
+                return None;
+            }
+            (
+                IpAddress::Ipv4(Ipv4Address::from(src_addr)),
+                IpAddress::Ipv4(Ipv4Address::from(dst_addr)),
+                len,
+            )
+        }
+        6 if packet.len() >= IPV6_HEADER_LEN => {
+            let src_addr = u128::from_be_bytes(packet[8..24].try_into().unwrap());
+            let dst_addr = u128::from_be_bytes(packet[24..40].try_into().unwrap());
+            let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+            (
+                IpAddress::Ipv6(Ipv6Address::from(src_addr)),
+                IpAddress::Ipv6(Ipv6Address::from(dst_addr)),
+                IPV6_HEADER_LEN + payload_len,
+            )
+        }
+        _ => return None,
+    };
+
+    if len > packet.len() {
+        return None;
+    }
+
+    Some((src_addr, dst_addr, len))
+}
+
+/// Wakes up the kernel thread of the device when the UDP sockets receive messages.
+struct WorkerWaker(Weak<WireGuard>);
+
+impl Observer<IoEvents> for WorkerWaker {
+    fn on_events(&self, _events: &IoEvents) {
+        if let Some(wireguard) = self.0.upgrade() {
+            wireguard.wake_worker();
+        }
+    }
+}
+
+/// The device of a WireGuard device.
+///
+/// The packets sent through the device are encrypted and sent to the peers, and the packets
+/// received from the device are those decrypted from the messages of the peers.
+struct WireGuardDevice(Arc<WireGuard>);
+
+impl Device for WireGuardDevice {
+    type RxToken<'a> = WireGuardRxToken;
+    type TxToken<'a> = WireGuardTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let packet = self.0.rx_queue.lock().pop_front()?;
+        Some((WireGuardRxToken(packet), WireGuardTxToken(&self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(WireGuardTxToken(&self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ip;
+        caps.max_transmission_unit = MTU;
+        caps
+    }
+}
+
+impl NotifyDevice for WireGuardDevice {
+    fn notify_poll_end(&mut self) {}
+}
+
+struct WireGuardRxToken(Vec<u8>);
+
+impl RxToken for WireGuardRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+struct WireGuardTxToken<'a>(&'a Arc<WireGuard>);
+
+impl TxToken for WireGuardTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut packet = vec![0u8; len];
+        let res = f(&mut packet);
+        self.0.transmit(packet);
+        res
+    }
+}
+
+struct WireGuardDriver(Arc<WireGuard>);
+
+impl WithDevice for WireGuardDriver {
+    type Device = WireGuardDevice;
+
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Device) -> R,
+    {
+        f(&mut WireGuardDevice(self.0.clone()))
+    }
+}
+
+/// The WireGuard devices, indexed by the indexes of their ifaces.
+static WIREGUARDS: Mutex<BTreeMap<u32, Arc<WireGuard>>> = Mutex::new(BTreeMap::new());
+
+/// Creates a WireGuard device with the name.
+///
+/// Like Linux, the device listens on a random port until a port is configured.
+pub fn new_wireguard(name: &str) -> Result<Arc<WireGuard>> {
+    let mut wireguards = WIREGUARDS.lock();
+
+    let wireguard = Arc::new(WireGuard {
+        iface: Once::new(),
+        state: Mutex::new(DeviceState {
+            identity: None,
+            listen_port: 0,
+            fwmark: 0,
+            sockets: Vec::new(),
+            peers: BTreeMap::new(),
+            allowed_ips: AllowedIps::new(),
+            indexes: IndexTable::new(),
+        }),
+        tx_queue: SpinLock::new(VecDeque::new()),
+        rx_queue: SpinLock::new(VecDeque::new()),
+        pollee: Pollee::new(),
+        wait_queue: WaitQueue::new(),
+        has_work: AtomicBool::new(false),
+        is_deleted: AtomicBool::new(false),
+    });
+
+    let (sockets, listen_port) = wireguard.open_sockets(0)?;
+    {
+        let mut state = wireguard.state.lock();
+        state.sockets = sockets;
+        state.listen_port = listen_port;
+    }
+
+    // FIXME: These flags are currently hardcoded.
+    // In the future, we should set appropriate values.
+    let flags = InterfaceFlags::UP
+        | InterfaceFlags::POINTOPOINT
+        | InterfaceFlags::RUNNING
+        | InterfaceFlags::NOARP
+        | InterfaceFlags::LOWER_UP;
+
+    let iface = IpIface::new(
+        WireGuardDriver(wireguard.clone()),
+        None,
+        name.to_string(),
+        PollScheduler::new(),
+        InterfaceType::NONE,
+        flags,
+    ) as Arc<Iface>;
+    wireguard.iface.call_once(|| Arc::downgrade(&iface));
+
+    register_iface(iface.clone())?;
+    wireguards.insert(iface.index(), wireguard.clone());
+
+    let worker = wireguard.clone();
+    ThreadOptions::new(move || worker.run()).spawn();
+
+    Ok(wireguard)
+}
+
+/// Deletes the WireGuard device.
+pub fn delete_wireguard(index: u32) -> Result<()> {
+    let Some(wireguard) = WIREGUARDS.lock().remove(&index) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the interface is not a WireGuard device");
+    };
+
+    wireguard.is_deleted.store(true, Ordering::Release);
+    wireguard.wake_worker();
+
+    // Close the sockets now, so that the port can be reused immediately.
+    wireguard.state.lock().sockets.clear();
+
+    if let Some(iface) = wireguard.iface() {
+        unregister_iface(&iface);
+    }
+
+    Ok(())
+}
+
+/// Finds the WireGuard device with the given iface index.
+pub fn find_wireguard(index: u32) -> Option<Arc<WireGuard>> {
+    WIREGUARDS.lock().get(&index).cloned()
+}
@@ -0,0 +1,423 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The handshake of WireGuard, which follows the `Noise_IKpsk2` pattern.
+//!
+//! The initiator sends an initiation message and the responder replies with a response message.
+//! After that, both sides derive the same pair of keys for the transport messages.
+//!
+//! Reference: <https://www.wireguard.com/papers/wireguard.pdf>, Section 5.4.
+
+use crate::{
+    prelude::*,
+    time::clocks::RealTimeClock,
+    util::{
+        crypto::{
+            blake2s::Blake2s,
+            chacha20poly1305::{self, TAG_SIZE},
+            curve25519::{x25519, x25519_base},
+        },
+        random::getrandom,
+    },
+};
+
+/// The size of keys and hashes in bytes.
+pub(super) const KEY_LEN: usize = 32;
+
+pub(super) type Key = [u8; KEY_LEN];
+
+/// A TAI64N timestamp, which is used to prevent initiations from being replayed.
+pub(super) type Timestamp = [u8; 12];
+
+pub(super) const MESSAGE_INITIATION: u8 = 1;
+pub(super) const MESSAGE_RESPONSE: u8 = 2;
+pub(super) const MESSAGE_COOKIE_REPLY: u8 = 3;
+pub(super) const MESSAGE_TRANSPORT: u8 = 4;
+
+/// The length of an initiation message.
+pub(super) const INITIATION_LEN: usize = 148;
+/// The length of a response message.
+pub(super) const RESPONSE_LEN: usize = 92;
+
+const MAC_LEN: usize = 16;
+
+const CONSTRUCTION: &[u8] = b"Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+const IDENTIFIER: &[u8] = b"WireGuard v1 zx2c4 Jason@zx2c4.com";
+const LABEL_MAC1: &[u8] = b"mac1----";
+
+/// The static identity of a device.
+pub(super) struct Identity {
+    private_key: Key,
+    public_key: Key,
+    /// The key of `mac1` in the messages sent to the device.
+    mac1_key: Key,
+}
+
+impl Identity {
+    /// Creates an identity from the private key, which is clamped like Linux.
+    pub(super) fn new(mut private_key: Key) -> Self {
+        private_key[0] &= 248;
+        private_key[31] &= 127;
+        private_key[31] |= 64;
+
+        let public_key = x25519_base(&private_key);
+        Self {
+            private_key,
+            public_key,
+            mac1_key: mac1_key(&public_key),
+        }
+    }
+
+    pub(super) fn private_key(&self) -> &Key {
+        &self.private_key
+    }
+
+    pub(super) fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+
+    /// Checks the `mac1` field of a handshake message sent to the device.
+    pub(super) fn verify_mac1(&self, message: &[u8]) -> bool {
+        let mac1_offset = message.len() - 2 * MAC_LEN;
+        let expected = mac(&self.mac1_key, &message[..mac1_offset]);
+        // The MAC is not secret, so it does not need to be compared in constant time.
+        message[mac1_offset..mac1_offset + MAC_LEN] == expected
+    }
+}
+
+/// The keys of the transport messages derived from a handshake.
+pub(super) struct SessionKeys {
+    pub(super) send: Key,
+    pub(super) recv: Key,
+}
+
+/// The state of the initiator while it is waiting for the response.
+pub(super) struct InitiatorHandshake {
+    state: SymmetricState,
+    ephemeral_private_key: Key,
+}
+
+/// An initiation message that has been consumed by the responder.
+pub(super) struct ConsumedInitiation {
+    state: SymmetricState,
+    ephemeral_public_key: Key,
+    /// The static public key of the initiator.
+    pub(super) peer_public_key: Key,
+    /// The index chosen by the initiator.
+    pub(super) sender_index: u32,
+    pub(super) timestamp: Timestamp,
+}
+
+/// Creates an initiation message to the peer.
+///
+/// Returns `None` if the Diffie-Hellman results are all zeros, which happens if the public key
+/// of the peer has a small order.
+pub(super) fn create_initiation(
+    identity: &Identity,
+    peer_public_key: &Key,
+    sender_index: u32,
+) -> Option<(InitiatorHandshake, [u8; INITIATION_LEN])> {
+    let mut message = [0; INITIATION_LEN];
+    message[0] = MESSAGE_INITIATION;
+    message[4..8].copy_from_slice(&sender_index.to_le_bytes());
+
+    let mut state = SymmetricState::new(peer_public_key);
+
+    let ephemeral_private_key = new_private_key();
+    let ephemeral_public_key = x25519_base(&ephemeral_private_key);
+    message[8..40].copy_from_slice(&ephemeral_public_key);
+    state.mix_key(&ephemeral_public_key);
+    state.mix_hash(&ephemeral_public_key);
+
+    let key = state.mix_key_and_get(&dh(&ephemeral_private_key, peer_public_key)?);
+    state.encrypt_and_hash(&key, &identity.public_key, &mut message[40..88]);
+
+    let key = state.mix_key_and_get(&dh(&identity.private_key, peer_public_key)?);
+    state.encrypt_and_hash(&key, &tai64n_now(), &mut message[88..116]);
+
+    let mac1 = mac(&mac1_key(peer_public_key), &message[..116]);
+    message[116..132].copy_from_slice(&mac1);
+
+    let handshake = InitiatorHandshake {
+        state,
+        ephemeral_private_key,
+    };
+    Some((handshake, message))
+}
+
+/// Consumes an initiation message sent to the device.
+///
+/// The caller should have checked `mac1`. The returned initiation should be further checked
+/// against the peer that the initiator claims to be.
+pub(super) fn consume_initiation(
+    identity: &Identity,
+    message: &[u8; INITIATION_LEN],
+) -> Option<ConsumedInitiation> {
+    let sender_index = u32::from_le_bytes(message[4..8].try_into().unwrap());
+
+    let mut state = SymmetricState::new(&identity.public_key);
+
+    let ephemeral_public_key: Key = message[8..40].try_into().unwrap();
+    state.mix_key(&ephemeral_public_key);
+    state.mix_hash(&ephemeral_public_key);
+
+    let key = state.mix_key_and_get(&dh(&identity.private_key, &ephemeral_public_key)?);
+    let mut peer_public_key = [0; KEY_LEN];
+    state.decrypt_and_hash(&key, &message[40..88], &mut peer_public_key)?;
+
+    let key = state.mix_key_and_get(&dh(&identity.private_key, &peer_public_key)?);
+    let mut timestamp = [0; 12];
+    state.decrypt_and_hash(&key, &message[88..116], &mut timestamp)?;
+
+    Some(ConsumedInitiation {
+        state,
+        ephemeral_public_key,
+        peer_public_key,
+        sender_index,
+        timestamp,
+    })
+}
+
+/// Creates a response message to the consumed initiation.
+///
+/// This finishes the handshake on the side of the responder.
+pub(super) fn create_response(
+    initiation: ConsumedInitiation,
+    preshared_key: &Key,
+    sender_index: u32,
+) -> Option<(SessionKeys, [u8; RESPONSE_LEN])> {
+    let mut message = [0; RESPONSE_LEN];
+    message[0] = MESSAGE_RESPONSE;
+    message[4..8].copy_from_slice(&sender_index.to_le_bytes());
+    message[8..12].copy_from_slice(&initiation.sender_index.to_le_bytes());
+
+    let mut state = initiation.state;
+
+    let ephemeral_private_key = new_private_key();
+    let ephemeral_public_key = x25519_base(&ephemeral_private_key);
+    message[12..44].copy_from_slice(&ephemeral_public_key);
+    state.mix_key(&ephemeral_public_key);
+    state.mix_hash(&ephemeral_public_key);
+
+    state.mix_key(&dh(
+        &ephemeral_private_key,
+        &initiation.ephemeral_public_key,
+    )?);
+    state.mix_key(&dh(&ephemeral_private_key, &initiation.peer_public_key)?);
+
+    let key = state.mix_psk(preshared_key);
+    state.encrypt_and_hash(&key, &[], &mut message[44..60]);
+
+    let mac1 = mac(&mac1_key(&initiation.peer_public_key), &message[..60]);
+    message[60..76].copy_from_slice(&mac1);
+
+    let [recv, send] = kdf(&state.chaining_key, &[]);
+    Some((SessionKeys { send, recv }, message))
+}
+
+/// Consumes a response message to the initiation of the device.
+///
+/// The caller should have checked `mac1` and found the handshake by the receiver index. This
+/// finishes the handshake on the side of the initiator.
+pub(super) fn consume_response(
+    identity: &Identity,
+    handshake: &InitiatorHandshake,
+    preshared_key: &Key,
+    message: &[u8; RESPONSE_LEN],
+) -> Option<SessionKeys> {
+    let mut state = handshake.state.clone();
+
+    let ephemeral_public_key: Key = message[12..44].try_into().unwrap();
+    state.mix_key(&ephemeral_public_key);
+    state.mix_hash(&ephemeral_public_key);
+
+    state.mix_key(&dh(
+        &handshake.ephemeral_private_key,
+        &ephemeral_public_key,
+    )?);
+    state.mix_key(&dh(&identity.private_key, &ephemeral_public_key)?);
+
+    let key = state.mix_psk(preshared_key);
+    state.decrypt_and_hash(&key, &message[44..60], &mut [])?;
+
+    let [send, recv] = kdf(&state.chaining_key, &[]);
+    Some(SessionKeys { send, recv })
+}
+
+/// The chaining key and the hash of a handshake.
+#[derive(Clone)]
+struct SymmetricState {
+    chaining_key: Key,
+    hash: Key,
+}
+
+impl SymmetricState {
+    fn new(responder_public_key: &Key) -> Self {
+        let chaining_key = hash(&[CONSTRUCTION]);
+        let hash = hash(&[&hash(&[&chaining_key, IDENTIFIER]), responder_public_key]);
+        Self { chaining_key, hash }
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = hash(&[&self.hash, data]);
+    }
+
+    fn mix_key(&mut self, input: &[u8]) {
+        let [chaining_key] = kdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+    }
+
+    /// Mixes the input into the chaining key and returns a key for encryption.
+    fn mix_key_and_get(&mut self, input: &[u8]) -> Key {
+        let [chaining_key, key] = kdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        key
+    }
+
+    /// Mixes the pre-shared key into the chaining key and the hash, and returns a key for
+    /// encryption.
+    fn mix_psk(&mut self, preshared_key: &Key) -> Key {
+        let [chaining_key, tau, key] = kdf(&self.chaining_key, preshared_key);
+        self.chaining_key = chaining_key;
+        self.mix_hash(&tau);
+        key
+    }
+
+    /// Encrypts the plaintext into `output`, which must be [`TAG_SIZE`] bytes longer.
+    fn encrypt_and_hash(&mut self, key: &Key, plaintext: &[u8], output: &mut [u8]) {
+        let (ciphertext, tag) = output.split_at_mut(plaintext.len());
+        ciphertext.copy_from_slice(plaintext);
+        tag.copy_from_slice(&chacha20poly1305::seal(key, 0, &self.hash, ciphertext));
+        self.mix_hash(output);
+    }
+
+    /// Decrypts the ciphertext into `output`, which must be [`TAG_SIZE`] bytes shorter.
+    fn decrypt_and_hash(&mut self, key: &Key, ciphertext: &[u8], output: &mut [u8]) -> Option<()> {
+        let (data, tag) = ciphertext.split_at(ciphertext.len() - TAG_SIZE);
+        output.copy_from_slice(data);
+        if !chacha20poly1305::open(key, 0, &self.hash, output, tag.try_into().unwrap()) {
+            return None;
+        }
+        self.mix_hash(ciphertext);
+        Some(())
+    }
+}
+
+fn hash(parts: &[&[u8]]) -> Key {
+    let mut hasher = Blake2s::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
+
+fn mac(key: &Key, data: &[u8]) -> [u8; MAC_LEN] {
+    let mut hasher = Blake2s::new_keyed(key, MAC_LEN);
+    hasher.update(data);
+    hasher.finalize()[..MAC_LEN].try_into().unwrap()
+}
+
+fn mac1_key(public_key: &Key) -> Key {
+    hash(&[LABEL_MAC1, public_key])
+}
+
+/// Computes HMAC-BLAKE2s, whose block size is 64 bytes.
+fn hmac(key: &Key, parts: &[&[u8]]) -> Key {
+    const BLOCK_LEN: usize = 64;
+
+    let mut ipad = [0x36; BLOCK_LEN];
+    let mut opad = [0x5c; BLOCK_LEN];
+    for (i, byte) in key.iter().enumerate() {
+        ipad[i] ^= byte;
+        opad[i] ^= byte;
+    }
+
+    let mut inner = Blake2s::new();
+    inner.update(&ipad);
+    for part in parts {
+        inner.update(part);
+    }
+
+    hash(&[&opad, &inner.finalize()])
+}
+
+/// Derives `N` keys from the chaining key and the input with HKDF.
+fn kdf<const N: usize>(chaining_key: &Key, input: &[u8]) -> [Key; N] {
+    let secret = hmac(chaining_key, &[input]);
+
+    // The keys are generated in order, each from the previous one.
+    let mut prev: Option<Key> = None;
+    core::array::from_fn(|i| {
+        let prev_key: &[u8] = prev.as_ref().map_or(&[], |key| key);
+        let key = hmac(&secret, &[prev_key, &[i as u8 + 1]]);
+        prev = Some(key);
+        key
+    })
+}
+
+/// Computes the Diffie-Hellman shared secret, rejecting the all-zero result.
+fn dh(private_key: &Key, public_key: &Key) -> Option<Key> {
+    let shared = x25519(private_key, public_key);
+    let is_zero = shared.iter().fold(0, |acc, byte| acc | byte) == 0;
+    (!core::hint::black_box(is_zero)).then_some(shared)
+}
+
+fn new_private_key() -> Key {
+    let mut key = [0; KEY_LEN];
+    getrandom(&mut key);
+    key
+}
+
+/// Returns the current TAI64N timestamp.
+///
+/// Like Linux, the nanoseconds are rounded down to a multiple of 2^24 (about 16.8 milliseconds),
+/// so that the timestamp does not leak the precise time of the system.
+fn tai64n_now() -> Timestamp {
+    const TAI64_EPOCH: u64 = 0x4000_0000_0000_000a;
+    const NANOS_GRANULARITY: u32 = 1 << 24;
+
+    let now = RealTimeClock::get().read_time();
+
+    let mut timestamp = [0; 12];
+    timestamp[..8].copy_from_slice(&(TAI64_EPOCH + now.as_secs()).to_be_bytes());
+    let nanos = now.subsec_nanos() & !(NANOS_GRANULARITY - 1);
+    timestamp[8..].copy_from_slice(&nanos.to_be_bytes());
+    timestamp
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn test_handshake() {
+        let initiator = Identity::new([1; KEY_LEN]);
+        let responder = Identity::new([2; KEY_LEN]);
+        let psk = [3; KEY_LEN];
+
+        let (handshake, initiation) = create_initiation(&initiator, responder.public_key(), 7)
+            .expect("the initiation should be created");
+        assert!(responder.verify_mac1(&initiation));
+        assert!(!initiator.verify_mac1(&initiation));
+
+        let consumed =
+            consume_initiation(&responder, &initiation).expect("the initiation should be consumed");
+        assert_eq!(&consumed.peer_public_key, initiator.public_key());
+        assert_eq!(consumed.sender_index, 7);
+
+        let (responder_keys, response) =
+            create_response(consumed, &psk, 9).expect("the response should be created");
+        assert!(initiator.verify_mac1(&response));
+        assert_eq!(u32::from_le_bytes(response[8..12].try_into().unwrap()), 7);
+
+        // A different pre-shared key results in a failed handshake.
+        assert!(consume_response(&initiator, &handshake, &[4; KEY_LEN], &response).is_none());
+
+        let initiator_keys = consume_response(&initiator, &handshake, &psk, &response)
+            .expect("the response should be consumed");
+        assert_eq!(initiator_keys.send, responder_keys.recv);
+        assert_eq!(initiator_keys.recv, responder_keys.send);
+        assert_ne!(initiator_keys.send, initiator_keys.recv);
+    }
+}
@@ -0,0 +1,599 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::collections::btree_map;
+use core::time::Duration;
+
+use aster_bigtcp::wire::IpEndpoint;
+
+use super::{
+    noise::{
+        self, ConsumedInitiation, Identity, InitiatorHandshake, Key, Timestamp, MESSAGE_TRANSPORT,
+        RESPONSE_LEN,
+    },
+    MTU,
+};
+use crate::{
+    prelude::*,
+    time::clocks::RealTimeClock,
+    util::{
+        crypto::chacha20poly1305::{self, TAG_SIZE},
+        random::getrandom,
+    },
+};
+
+// The constants of the timers.
+//
+// Reference: <https://www.wireguard.com/papers/wireguard.pdf>, Section 6.1.
+const REKEY_AFTER_MESSAGES: u64 = 1 << 60;
+const REJECT_AFTER_MESSAGES: u64 = u64::MAX - (1 << 13);
+const REKEY_AFTER_TIME: Duration = Duration::from_secs(120);
+const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
+const REKEY_ATTEMPT_TIME: Duration = Duration::from_secs(90);
+const REKEY_TIMEOUT: Duration = Duration::from_secs(5);
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The minimum interval between two initiations consumed from the same peer.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/wireguard/messages.h#L48>.
+const MIN_INITIATION_INTERVAL: Duration = Duration::from_millis(1000 / 50);
+
+/// The maximum number of packets that wait for a handshake to finish.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/wireguard/messages.h#L113>.
+const MAX_STAGED_PACKETS: usize = 128;
+
+/// The length of the header of a transport message.
+pub(super) const TRANSPORT_HEADER_LEN: usize = 16;
+
+/// The context in which the messages of a peer are processed.
+pub(super) struct PeerContext<'a> {
+    pub(super) identity: &'a Identity,
+    pub(super) indexes: &'a mut IndexTable,
+    /// The current monotonic time.
+    pub(super) now: Duration,
+    /// The messages that should be sent to the peer.
+    pub(super) messages: Vec<Vec<u8>>,
+}
+
+/// The local indexes of the handshakes and the keypairs.
+///
+/// The indexes are chosen randomly and are sent to the peers, which use them to tell which
+/// handshake or keypair their messages belong to.
+pub(super) struct IndexTable(BTreeMap<u32, Key>);
+
+impl IndexTable {
+    pub(super) const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Returns the public key of the peer that the index belongs to.
+    pub(super) fn get(&self, index: u32) -> Option<&Key> {
+        self.0.get(&index)
+    }
+
+    fn alloc(&mut self, peer: &Key) -> u32 {
+        loop {
+            let mut bytes = [0; 4];
+            getrandom(&mut bytes);
+            let index = u32::from_ne_bytes(bytes);
+            if let btree_map::Entry::Vacant(entry) = self.0.entry(index) {
+                entry.insert(*peer);
+                return index;
+            }
+        }
+    }
+
+    fn free(&mut self, index: u32) {
+        self.0.remove(&index);
+    }
+}
+
+/// A peer of a device.
+pub(super) struct Peer {
+    public_key: Key,
+    pub(super) preshared_key: Key,
+    pub(super) endpoint: Option<IpEndpoint>,
+    /// The interval of the persistent keepalives in seconds, or zero if they are disabled.
+    pub(super) persistent_keepalive_interval: u16,
+    pub(super) rx_bytes: u64,
+    pub(super) tx_bytes: u64,
+    /// The wall-clock time of the last handshake that has finished.
+    pub(super) last_handshake_time: Option<Duration>,
+    /// The greatest timestamp of the initiations from the peer.
+    latest_timestamp: Timestamp,
+    /// The time when the last initiation from the peer was consumed.
+    last_initiation_consumed: Option<Duration>,
+    /// The handshake initiated by the device, which is waiting for the response.
+    handshake: Option<PendingHandshake>,
+    /// The keypair that is used to send messages.
+    current: Option<Keypair>,
+    /// The keypair that was replaced by the current one, which can still receive messages.
+    previous: Option<Keypair>,
+    /// The keypair negotiated by a handshake initiated by the peer, which is used to send
+    /// messages once the peer has sent a message with it.
+    next: Option<Keypair>,
+    /// The packets that wait for a handshake to finish.
+    staged_packets: VecDeque<Vec<u8>>,
+    /// The time when the last message was sent.
+    last_sent: Option<Duration>,
+    /// The time when data was sent without any message received after it.
+    unanswered_since: Option<Duration>,
+    /// The time when data was received without any message sent after it.
+    unreplied_since: Option<Duration>,
+}
+
+struct PendingHandshake {
+    local_index: u32,
+    state: InitiatorHandshake,
+    /// The time when the initiation was sent.
+    sent_at: Duration,
+    /// The time when the first initiation of the handshake attempt was sent.
+    started_at: Duration,
+}
+
+/// The keys and the counters of a session between the device and the peer.
+struct Keypair {
+    local_index: u32,
+    remote_index: u32,
+    send_key: Key,
+    recv_key: Key,
+    send_counter: u64,
+    replay_window: ReplayWindow,
+    created_at: Duration,
+    is_initiator: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Current,
+    Previous,
+    Next,
+}
+
+impl Peer {
+    pub(super) fn new(public_key: Key) -> Self {
+        Self {
+            public_key,
+            preshared_key: [0; noise::KEY_LEN],
+            endpoint: None,
+            persistent_keepalive_interval: 0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            last_handshake_time: None,
+            latest_timestamp: [0; 12],
+            last_initiation_consumed: None,
+            handshake: None,
+            current: None,
+            previous: None,
+            next: None,
+            staged_packets: VecDeque::new(),
+            last_sent: None,
+            unanswered_since: None,
+            unreplied_since: None,
+        }
+    }
+
+    /// Drops the handshake and the keypairs, and releases their indexes.
+    ///
+    /// This is necessary if the peer is removed or the identity of the device changes.
+    pub(super) fn clear_sessions(&mut self, indexes: &mut IndexTable) {
+        if let Some(handshake) = self.handshake.take() {
+            indexes.free(handshake.local_index);
+        }
+        for slot in [Slot::Current, Slot::Previous, Slot::Next] {
+            if let Some(keypair) = self.keypair_mut(slot).take() {
+                indexes.free(keypair.local_index);
+            }
+        }
+    }
+
+    /// Sends the packet to the peer.
+    ///
+    /// The packet is staged if there is no keypair to encrypt it, in which case a handshake is
+    /// initiated. An empty packet is a keepalive.
+    pub(super) fn send_packet(&mut self, packet: Vec<u8>, cx: &mut PeerContext) {
+        if self.staged_packets.len() >= MAX_STAGED_PACKETS {
+            self.staged_packets.pop_front();
+        }
+        self.staged_packets.push_back(packet);
+
+        self.send_staged_packets(cx);
+    }
+
+    fn send_staged_packets(&mut self, cx: &mut PeerContext) {
+        let now = cx.now;
+
+        let Some(keypair) = self
+            .current
+            .as_mut()
+            .filter(|keypair| keypair.can_send(now))
+        else {
+            self.initiate_handshake(cx, false);
+            return;
+        };
+
+        while let Some(packet) = self.staged_packets.pop_front() {
+            if !packet.is_empty() && self.unanswered_since.is_none() {
+                self.unanswered_since = Some(now);
+            }
+            cx.messages.push(keypair.encrypt(&packet));
+        }
+        self.last_sent = Some(now);
+        self.unreplied_since = None;
+
+        // Like Linux, only the initiator renews the keypair when it is old, so that both sides
+        // do not initiate handshakes at the same time.
+        let needs_rekey = keypair.send_counter >= REKEY_AFTER_MESSAGES
+            || (keypair.is_initiator && now - keypair.created_at >= REKEY_AFTER_TIME);
+        if needs_rekey {
+            self.initiate_handshake(cx, false);
+        }
+    }
+
+    /// Initiates a handshake with the peer.
+    ///
+    /// If `is_retry` is false and an initiation has been sent recently, nothing is done.
+    fn initiate_handshake(&mut self, cx: &mut PeerContext, is_retry: bool) {
+        let now = cx.now;
+
+        let started_at = match &self.handshake {
+            Some(handshake) if is_retry => handshake.started_at,
+            Some(handshake) if now - handshake.sent_at < REKEY_TIMEOUT => return,
+            _ => now,
+        };
+        if let Some(handshake) = self.handshake.take() {
+            cx.indexes.free(handshake.local_index);
+        }
+
+        let local_index = cx.indexes.alloc(&self.public_key);
+        let Some((state, message)) =
+            noise::create_initiation(cx.identity, &self.public_key, local_index)
+        else {
+            cx.indexes.free(local_index);
+            return;
+        };
+
+        self.handshake = Some(PendingHandshake {
+            local_index,
+            state,
+            sent_at: now,
+            started_at,
+        });
+        cx.messages.push(message.to_vec());
+        self.last_sent = Some(now);
+    }
+
+    /// Replies to the initiation from the peer.
+    ///
+    /// Returns whether the initiation is accepted.
+    pub(super) fn consume_initiation(
+        &mut self,
+        initiation: ConsumedInitiation,
+        cx: &mut PeerContext,
+    ) -> bool {
+        let now = cx.now;
+
+        // Reject the replayed initiations and the initiations that come too fast.
+        if initiation.timestamp <= self.latest_timestamp {
+            return false;
+        }
+        if self
+            .last_initiation_consumed
+            .is_some_and(|time| now - time < MIN_INITIATION_INTERVAL)
+        {
+            return false;
+        }
+
+        let timestamp = initiation.timestamp;
+        let remote_index = initiation.sender_index;
+        let local_index = cx.indexes.alloc(&self.public_key);
+        let Some((keys, message)) =
+            noise::create_response(initiation, &self.preshared_key, local_index)
+        else {
+            cx.indexes.free(local_index);
+            return false;
+        };
+
+        self.latest_timestamp = timestamp;
+        self.last_initiation_consumed = Some(now);
+
+        let keypair = Keypair {
+            local_index,
+            remote_index,
+            send_key: keys.send,
+            recv_key: keys.recv,
+            send_counter: 0,
+            replay_window: ReplayWindow::new(),
+            created_at: now,
+            is_initiator: false,
+        };
+        if let Some(old_keypair) = self.next.replace(keypair) {
+            cx.indexes.free(old_keypair.local_index);
+        }
+
+        cx.messages.push(message.to_vec());
+        self.last_sent = Some(now);
+
+        true
+    }
+
+    /// Finishes the handshake initiated by the device with the response from the peer.
+    ///
+    /// Returns whether the response is accepted.
+    pub(super) fn consume_response(
+        &mut self,
+        message: &[u8; RESPONSE_LEN],
+        cx: &mut PeerContext,
+    ) -> bool {
+        let receiver_index = u32::from_le_bytes(message[8..12].try_into().unwrap());
+        let Some(handshake) = self
+            .handshake
+            .take_if(|handshake| handshake.local_index == receiver_index)
+        else {
+            return false;
+        };
+
+        let Some(keys) =
+            noise::consume_response(cx.identity, &handshake.state, &self.preshared_key, message)
+        else {
+            self.handshake = Some(handshake);
+            return false;
+        };
+
+        let keypair = Keypair {
+            local_index: handshake.local_index,
+            remote_index: u32::from_le_bytes(message[4..8].try_into().unwrap()),
+            send_key: keys.send,
+            recv_key: keys.recv,
+            send_counter: 0,
+            replay_window: ReplayWindow::new(),
+            created_at: cx.now,
+            is_initiator: true,
+        };
+        self.install_keypair(keypair, cx.indexes);
+        if let Some(next) = self.next.take() {
+            cx.indexes.free(next.local_index);
+        }
+        self.unanswered_since = None;
+
+        // Send a keepalive if there are no staged packets, so that the peer knows that the
+        // handshake has finished.
+        if self.staged_packets.is_empty() {
+            self.staged_packets.push_back(Vec::new());
+        }
+        self.send_staged_packets(cx);
+
+        true
+    }
+
+    /// Decrypts the transport message from the peer.
+    ///
+    /// Returns the decrypted packet, which may have padding at the end and is empty if the
+    /// message is a keepalive. Returns `None` if the message is invalid.
+    pub(super) fn consume_transport(
+        &mut self,
+        message: &mut [u8],
+        cx: &mut PeerContext,
+    ) -> Option<Vec<u8>> {
+        let now = cx.now;
+
+        let receiver_index = u32::from_le_bytes(message[4..8].try_into().unwrap());
+        let counter = u64::from_le_bytes(message[8..16].try_into().unwrap());
+
+        let slot = [Slot::Current, Slot::Previous, Slot::Next]
+            .into_iter()
+            .find(|slot| {
+                self.keypair_mut(*slot)
+                    .as_ref()
+                    .is_some_and(|keypair| keypair.local_index == receiver_index)
+            })?;
+        let keypair = self.keypair_mut(slot).as_mut().unwrap();
+        if now - keypair.created_at >= REJECT_AFTER_TIME {
+            return None;
+        }
+
+        let data_len = message.len() - TRANSPORT_HEADER_LEN - TAG_SIZE;
+        let (data, tag) = message[TRANSPORT_HEADER_LEN..].split_at_mut(data_len);
+        if !chacha20poly1305::open(
+            &keypair.recv_key,
+            counter,
+            &[],
+            data,
+            (&*tag).try_into().unwrap(),
+        ) {
+            return None;
+        }
+        if !keypair.replay_window.check_and_update(counter) {
+            return None;
+        }
+        let packet = data.to_vec();
+
+        self.unanswered_since = None;
+        if !packet.is_empty() && self.unreplied_since.is_none() {
+            self.unreplied_since = Some(now);
+        }
+
+        // The keypair negotiated by the handshake initiated by the peer is confirmed.
+        if let Slot::Next = slot {
+            let keypair = self.next.take().unwrap();
+            self.install_keypair(keypair, cx.indexes);
+            self.send_staged_packets(cx);
+        }
+
+        Some(packet)
+    }
+
+    /// Handles the timers of the peer.
+    ///
+    /// This should be called periodically.
+    pub(super) fn tick(&mut self, cx: &mut PeerContext) {
+        let now = cx.now;
+
+        // Retransmit the initiation if no response is received, or give up.
+        if let Some(handshake) = &self.handshake {
+            if now - handshake.started_at >= REKEY_ATTEMPT_TIME {
+                cx.indexes.free(handshake.local_index);
+                self.handshake = None;
+                self.staged_packets.clear();
+            } else if now - handshake.sent_at >= REKEY_TIMEOUT {
+                self.initiate_handshake(cx, true);
+            }
+        }
+
+        // Initiate a new handshake if the peer does not respond to the data.
+        if self
+            .unanswered_since
+            .is_some_and(|time| now - time >= KEEPALIVE_TIMEOUT + REKEY_TIMEOUT)
+        {
+            self.unanswered_since = None;
+            self.initiate_handshake(cx, false);
+        }
+
+        // Send a keepalive if there is no data to reply to the peer.
+        if self
+            .unreplied_since
+            .is_some_and(|time| now - time >= KEEPALIVE_TIMEOUT)
+        {
+            self.unreplied_since = None;
+            self.send_packet(Vec::new(), cx);
+        }
+
+        // Send a persistent keepalive, which keeps the NAT mappings alive.
+        let interval = Duration::from_secs(self.persistent_keepalive_interval.into());
+        if self.persistent_keepalive_interval > 0
+            && self.endpoint.is_some()
+            && self.last_sent.is_none_or(|time| now - time >= interval)
+        {
+            self.send_packet(Vec::new(), cx);
+        }
+
+        // Erase the keypairs that can no longer be used.
+        for slot in [Slot::Current, Slot::Previous, Slot::Next] {
+            let keypair = self.keypair_mut(slot);
+            if keypair
+                .as_ref()
+                .is_some_and(|keypair| now - keypair.created_at >= REJECT_AFTER_TIME * 3)
+            {
+                cx.indexes.free(keypair.take().unwrap().local_index);
+            }
+        }
+    }
+
+    /// Makes the keypair the current one.
+    fn install_keypair(&mut self, keypair: Keypair, indexes: &mut IndexTable) {
+        if let Some(previous) = self.previous.take() {
+            indexes.free(previous.local_index);
+        }
+        self.previous = self.current.replace(keypair);
+        self.last_handshake_time = Some(RealTimeClock::get().read_time());
+    }
+
+    fn keypair_mut(&mut self, slot: Slot) -> &mut Option<Keypair> {
+        match slot {
+            Slot::Current => &mut self.current,
+            Slot::Previous => &mut self.previous,
+            Slot::Next => &mut self.next,
+        }
+    }
+
+    pub(super) fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+}
+
+impl Keypair {
+    fn can_send(&self, now: Duration) -> bool {
+        self.send_counter < REJECT_AFTER_MESSAGES && now - self.created_at < REJECT_AFTER_TIME
+    }
+
+    /// Encrypts the packet into a transport message.
+    fn encrypt(&mut self, packet: &[u8]) -> Vec<u8> {
+        // Pad the packet to a multiple of 16 bytes, but not beyond the MTU.
+        let padded_len = packet.len().next_multiple_of(16).min(MTU).max(packet.len());
+
+        let mut message = vec![0; TRANSPORT_HEADER_LEN + padded_len + TAG_SIZE];
+        message[0] = MESSAGE_TRANSPORT;
+        message[4..8].copy_from_slice(&self.remote_index.to_le_bytes());
+        message[8..16].copy_from_slice(&self.send_counter.to_le_bytes());
+
+        let (data, tag) = message[TRANSPORT_HEADER_LEN..].split_at_mut(padded_len);
+        data[..packet.len()].copy_from_slice(packet);
+        tag.copy_from_slice(&chacha20poly1305::seal(
+            &self.send_key,
+            self.send_counter,
+            &[],
+            data,
+        ));
+
+        self.send_counter += 1;
+        message
+    }
+}
+
+/// The sliding window of the counters of the received messages.
+///
+/// The window rejects the replayed messages, while still allowing the messages to be reordered
+/// to some extent.
+///
+/// Reference: <https://www.rfc-editor.org/rfc/rfc6479>.
+struct ReplayWindow {
+    /// The greatest counter that has been received.
+    greatest: Option<u64>,
+    bitmap: [u64; WINDOW_WORDS],
+}
+
+const WINDOW_WORDS: usize = 32;
+const WINDOW_SIZE: u64 = WINDOW_WORDS as u64 * u64::BITS as u64;
+
+impl ReplayWindow {
+    fn new() -> Self {
+        Self {
+            greatest: None,
+            bitmap: [0; WINDOW_WORDS],
+        }
+    }
+
+    /// Checks the counter and records it.
+    ///
+    /// Returns false if the counter has been received or is too old.
+    fn check_and_update(&mut self, counter: u64) -> bool {
+        if counter >= REJECT_AFTER_MESSAGES {
+            return false;
+        }
+
+        match self.greatest {
+            Some(greatest) if counter <= greatest => {
+                if greatest - counter >= WINDOW_SIZE {
+                    return false;
+                }
+            }
+            _ => {
+                // Clear the bits of the counters that slide into the window.
+                let start = self.greatest.map_or(0, |greatest| greatest + 1);
+                if counter - start >= WINDOW_SIZE {
+                    self.bitmap.fill(0);
+                } else {
+                    for skipped in start..=counter {
+                        let (word, bit) = Self::position(skipped);
+                        self.bitmap[word] &= !bit;
+                    }
+                }
+                self.greatest = Some(counter);
+            }
+        }
+
+        let (word, bit) = Self::position(counter);
+        if self.bitmap[word] & bit != 0 {
+            return false;
+        }
+        self.bitmap[word] |= bit;
+
+        true
+    }
+
+    fn position(counter: u64) -> (usize, u64) {
+        let word = (counter / u64::BITS as u64) as usize % WINDOW_WORDS;
+        let bit = 1 << (counter % u64::BITS as u64);
+        (word, bit)
+    }
+}
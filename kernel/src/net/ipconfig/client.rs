@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The DHCP client, which acquires and renews the leases.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc2131#section-4.4>.
+
+use core::time::Duration;
+
+use aster_bigtcp::{
+    iface::{BindPortConfig, RxHandler},
+    wire::{EthernetAddress, IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr},
+};
+use aster_softirq::BottomHalfDisabled;
+use aster_time::read_monotonic_time;
+use ostd::sync::WaitQueue;
+
+use super::message::{
+    build_frame, parse_frame, ClientMessage, MessageType, ServerMessage, DHCP_CLIENT_PORT,
+    DHCP_SERVER_PORT,
+};
+use crate::{
+    events::{IoEvents, Observer},
+    net::{
+        iface::{Iface, UdpSocket},
+        socket::ip::datagram::DatagramObserver,
+    },
+    prelude::*,
+    process::signal::{PollAdaptor, Pollee},
+    util::random::getrandom,
+    WaitTimeout,
+};
+
+/// The timeout of the first attempt, which is doubled after each attempt.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/ipconfig.c#L94>.
+const BASE_TIMEOUT: Duration = Duration::from_secs(2);
+/// The maximum timeout of an attempt.
+const MAX_TIMEOUT: Duration = Duration::from_secs(30);
+/// The number of the attempts before giving up.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// The minimum interval between the retransmissions when the lease is renewed.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum number of the frames that are queued before they are handled.
+const MAX_QUEUED_FRAMES: usize = 16;
+
+/// A lease acquired from a DHCP server.
+#[derive(Debug, Clone)]
+pub(super) struct Lease {
+    pub(super) addr: Ipv4Cidr,
+    pub(super) router: Option<Ipv4Address>,
+    pub(super) dns_servers: Vec<Ipv4Address>,
+    pub(super) domain_name: Option<String>,
+    /// The server that grants the lease.
+    pub(super) server: Ipv4Address,
+    /// The time when the request for the lease is sent, from which the other times count.
+    pub(super) acquired_at: Duration,
+    /// The duration of the lease, where `None` means infinity.
+    pub(super) lease_time: Option<Duration>,
+    /// The time after which the lease is renewed with the server (T1).
+    pub(super) renewal_time: Duration,
+    /// The time after which the lease is renewed with any server (T2).
+    pub(super) rebinding_time: Duration,
+}
+
+impl Lease {
+    fn from_ack(ack: &ServerMessage, acquired_at: Duration) -> Option<Self> {
+        let addr = ack.yiaddr?;
+        let server = ack.server_id.or(ack.siaddr)?;
+
+        // Guess the prefix from the class of the address if the server does not tell it.
+        let prefix_len = match ack.subnet_mask {
+            Some(mask) => {
+                let mask = u32::from(mask);
+                if mask.leading_ones() != mask.count_ones() {
+                    return None;
+                }
+                mask.leading_ones() as u8
+            }
+            None if addr.octets()[0] < 128 => 8,
+            None if addr.octets()[0] < 192 => 16,
+            None => 24,
+        };
+
+        // The times are the same as the defaults in RFC 2131.
+        let lease_time = match ack.lease_time {
+            Some(u32::MAX) => None,
+            Some(secs) => Some(Duration::from_secs(secs as u64)),
+            None => return None,
+        };
+        let lease_or_max = lease_time.unwrap_or(Duration::MAX);
+        let renewal_time = ack
+            .renewal_time
+            .map(|secs| Duration::from_secs(secs as u64))
+            .unwrap_or(lease_or_max / 2)
+            .min(lease_or_max);
+        let rebinding_time = ack
+            .rebinding_time
+            .map(|secs| Duration::from_secs(secs as u64))
+            .unwrap_or(lease_or_max / 8 * 7)
+            .clamp(renewal_time, lease_or_max);
+
+        Some(Self {
+            addr: Ipv4Cidr::new(addr, prefix_len),
+            router: ack.routers.first().copied(),
+            dns_servers: ack.dns_servers.clone(),
+            domain_name: ack.domain_name.clone(),
+            server,
+            acquired_at,
+            lease_time,
+            renewal_time,
+            rebinding_time,
+        })
+    }
+
+    /// Returns the time when the lease expires, or `None` if the lease never expires.
+    pub(super) fn expires_at(&self) -> Option<Duration> {
+        self.lease_time
+            .map(|lease_time| self.acquired_at.saturating_add(lease_time))
+    }
+
+    pub(super) fn renew_at(&self) -> Duration {
+        self.acquired_at.saturating_add(self.renewal_time)
+    }
+
+    fn rebind_at(&self) -> Duration {
+        self.acquired_at.saturating_add(self.rebinding_time)
+    }
+}
+
+/// Acquires a lease for an iface that has no IPv4 addresses.
+///
+/// The messages are exchanged in raw frames, during which the iface does not receive any
+/// packets.
+pub(super) fn acquire(iface: &Arc<Iface>) -> Result<Lease> {
+    let Some(ether_addr) = iface.ether_addr() else {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "the interface is not an Ethernet interface"
+        );
+    };
+
+    let receiver = Arc::new(FrameReceiver {
+        frames: SpinLock::new(VecDeque::new()),
+        wait_queue: WaitQueue::new(),
+    });
+    if !iface.register_rx_handler(receiver.clone()) {
+        return_errno_with_message!(
+            Errno::EBUSY,
+            "the interface is enslaved to another interface"
+        );
+    }
+
+    let res = Transaction::new(ether_addr).acquire(iface, &receiver);
+
+    iface.unregister_rx_handler();
+
+    res
+}
+
+/// Renews the lease with the servers until the lease expires.
+///
+/// Returns the new lease, or `None` if the lease expires or is revoked by the server.
+pub(super) fn renew(iface: &Arc<Iface>, lease: &Lease) -> Result<Option<Lease>> {
+    let Some(expires_at) = lease.expires_at() else {
+        return Ok(Some(lease.clone()));
+    };
+    let Some(ether_addr) = iface.ether_addr() else {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "the interface is not an Ethernet interface"
+        );
+    };
+
+    let pollee = Pollee::new();
+    let wait_queue = Arc::new(WaitQueue::new());
+    let mut poll_adaptor = PollAdaptor::with_observer(SocketWaker(wait_queue.clone()));
+    pollee.register_poller(poll_adaptor.as_handle_mut(), IoEvents::IN);
+
+    let bound_port = iface.bind(
+        IpAddress::Ipv4(lease.addr.address()),
+        BindPortConfig::new(DHCP_CLIENT_PORT, false),
+    )?;
+    let socket = match UdpSocket::new_bind(bound_port, DatagramObserver::new(pollee)) {
+        Ok(socket) => socket,
+        Err((_, err)) => {
+            unreachable!("`new_bind` fails with {:?}, which should not happen", err)
+        }
+    };
+
+    let transaction = Transaction::new(ether_addr);
+    let request = ClientMessage {
+        message_type: MessageType::Request,
+        xid: transaction.xid,
+        chaddr: ether_addr,
+        ciaddr: lease.addr.address(),
+        requested_ip: None,
+        server_id: None,
+    }
+    .to_bytes();
+
+    loop {
+        let now = read_monotonic_time();
+        if now >= expires_at {
+            return Ok(None);
+        }
+
+        // In the RENEWING state, the request is unicast to the server that grants the lease.
+        // Otherwise, the request is broadcast to any server in the REBINDING state.
+        let rebind_at = lease.rebind_at();
+        let (dst, state_ends_at) = if now < rebind_at {
+            (lease.server, rebind_at)
+        } else {
+            (Ipv4Address::BROADCAST, expires_at)
+        };
+        let endpoint = IpEndpoint::new(IpAddress::Ipv4(dst), DHCP_SERVER_PORT);
+        if let Err(err) = socket.send(request.len(), endpoint, |buffer| {
+            buffer.copy_from_slice(&request)
+        }) {
+            debug!("failed to send the DHCP request: {:?}", err);
+        }
+        iface.poll();
+
+        // Wait for half of the remaining time in the state, but no less than one minute.
+        let remaining = state_ends_at - now;
+        let timeout = (remaining / 2).max(MIN_RENEW_INTERVAL).min(remaining);
+        let reply = wait_queue.wait_until_or_timeout(
+            || loop {
+                let message = socket.recv(|data, _, _| ServerMessage::parse(data)).ok()?;
+                if let Some(message) = message.filter(|message| transaction.matches(message)) {
+                    return Some(message);
+                }
+            },
+            &timeout,
+        );
+
+        match reply {
+            Ok(reply) if reply.message_type == Some(MessageType::Ack) => {
+                if let Some(new_lease) = Lease::from_ack(&reply, now) {
+                    return Ok(Some(new_lease));
+                }
+            }
+            Ok(reply) if reply.message_type == Some(MessageType::Nak) => return Ok(None),
+            _ => (),
+        }
+    }
+}
+
+/// A DHCP transaction, which is identified by a random ID.
+struct Transaction {
+    xid: u32,
+    ether_addr: EthernetAddress,
+}
+
+impl Transaction {
+    fn new(ether_addr: EthernetAddress) -> Self {
+        let mut xid = [0u8; 4];
+        getrandom(&mut xid);
+
+        Self {
+            xid: u32::from_ne_bytes(xid),
+            ether_addr,
+        }
+    }
+
+    /// Returns whether the message is a reply to this transaction.
+    fn matches(&self, message: &ServerMessage) -> bool {
+        message.xid == self.xid && message.chaddr == self.ether_addr.0
+    }
+
+    fn acquire(&self, iface: &Arc<Iface>, receiver: &FrameReceiver) -> Result<Lease> {
+        let mut timeout = BASE_TIMEOUT;
+
+        for _ in 0..MAX_ATTEMPTS {
+            if let Some(lease) = self.try_acquire(iface, receiver, timeout) {
+                return Ok(lease);
+            }
+            timeout = (timeout * 2).min(MAX_TIMEOUT);
+        }
+
+        return_errno_with_message!(Errno::ETIMEDOUT, "no DHCP server replies");
+    }
+
+    /// Tries to acquire a lease by discovering the servers and requesting the offered address.
+    ///
+    /// Returns `None` if no server replies in time or the server refuses the request.
+    fn try_acquire(
+        &self,
+        iface: &Arc<Iface>,
+        receiver: &FrameReceiver,
+        timeout: Duration,
+    ) -> Option<Lease> {
+        // Drop the stale replies of the previous attempts.
+        receiver.frames.lock().clear();
+
+        let discover = ClientMessage {
+            message_type: MessageType::Discover,
+            xid: self.xid,
+            chaddr: self.ether_addr,
+            ciaddr: Ipv4Address::UNSPECIFIED,
+            requested_ip: None,
+            server_id: None,
+        };
+        self.send(iface, &discover);
+        let offer = self.wait_for_reply(receiver, &[MessageType::Offer], timeout)?;
+
+        let requested_at = read_monotonic_time();
+        let request = ClientMessage {
+            message_type: MessageType::Request,
+            xid: self.xid,
+            chaddr: self.ether_addr,
+            ciaddr: Ipv4Address::UNSPECIFIED,
+            requested_ip: offer.yiaddr,
+            server_id: offer.server_id,
+        };
+        self.send(iface, &request);
+        let ack = self.wait_for_reply(receiver, &[MessageType::Ack, MessageType::Nak], timeout)?;
+        if ack.message_type != Some(MessageType::Ack) {
+            debug!("the DHCP server refuses the request: {:?}", ack);
+            return None;
+        }
+
+        Lease::from_ack(&ack, requested_at)
+    }
+
+    fn send(&self, iface: &Arc<Iface>, message: &ClientMessage) {
+        let frame = build_frame(self.ether_addr, &message.to_bytes());
+        if !iface.send_frame(&frame) {
+            debug!("failed to send the DHCP message: the device is busy");
+        }
+    }
+
+    fn wait_for_reply(
+        &self,
+        receiver: &FrameReceiver,
+        types: &[MessageType],
+        timeout: Duration,
+    ) -> Option<ServerMessage> {
+        receiver
+            .wait_queue
+            .wait_until_or_timeout(
+                || loop {
+                    let frame = receiver.frames.lock().pop_front()?;
+                    let Some(message) = parse_frame(&frame).and_then(ServerMessage::parse) else {
+                        continue;
+                    };
+                    if self.matches(&message)
+                        && message
+                            .message_type
+                            .is_some_and(|message_type| types.contains(&message_type))
+                    {
+                        return Some(message);
+                    }
+                },
+                &timeout,
+            )
+            .ok()
+    }
+}
+
+/// The handler that takes over the frames received by the iface while a lease is acquired.
+struct FrameReceiver {
+    frames: SpinLock<VecDeque<Vec<u8>>, BottomHalfDisabled>,
+    wait_queue: WaitQueue,
+}
+
+impl RxHandler for FrameReceiver {
+    fn handle_frame(&self, _iface_index: u32, frame: &[u8]) {
+        // Most of the frames are irrelevant, so they are filtered before being queued.
+        if parse_frame(frame).is_none() {
+            return;
+        }
+
+        let mut frames = self.frames.lock();
+        if frames.len() < MAX_QUEUED_FRAMES {
+            frames.push_back(frame.to_vec());
+        }
+        drop(frames);
+
+        self.wait_queue.wake_all();
+    }
+}
+
+/// The observer that wakes up the waiter when the socket receives messages.
+struct SocketWaker(Arc<WaitQueue>);
+
+impl Observer<IoEvents> for SocketWaker {
+    fn on_events(&self, _events: &IoEvents) {
+        self.0.wake_all();
+    }
+}
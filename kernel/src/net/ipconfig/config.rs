@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `ip` argument of the kernel command line.
+//!
+//! The argument is either the autoconfiguration protocol (e.g., `ip=dhcp`) or the fields separated
+//! by colons, which is the same as Linux:
+//!
+//! ```text
+//! ip=<client-ip>:<server-ip>:<gw-ip>:<netmask>:<hostname>:<device>:<autoconf>:<dns0-ip>:<dns1-ip>:<ntp0-ip>
+//! ```
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/nfs/nfsroot.html>.
+
+use crate::prelude::*;
+
+/// The configuration specified by the `ip` argument.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct IpConfig {
+    /// The name of the iface, where `None` means `eth0`.
+    pub(super) device: Option<String>,
+    pub(super) autoconf: Autoconf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Autoconf {
+    Off,
+    Dhcp,
+}
+
+/// The indexes of the fields.
+const DEVICE: usize = 5;
+const AUTOCONF: usize = 6;
+
+impl IpConfig {
+    pub(super) fn parse(value: &str) -> Result<Self> {
+        if !value.contains(':') {
+            return Ok(Self {
+                device: None,
+                autoconf: parse_autoconf(value)?,
+            });
+        }
+
+        let fields: Vec<&str> = value.split(':').collect();
+        for (index, field) in fields.iter().enumerate() {
+            if !field.is_empty() && index != DEVICE && index != AUTOCONF {
+                warn!("[ipconfig] the field {} of `ip` is not supported", index);
+            }
+        }
+
+        let device = fields
+            .get(DEVICE)
+            .filter(|device| !device.is_empty())
+            .map(|device| device.to_string());
+        // Like Linux, an empty protocol means any protocol.
+        let autoconf = match fields.get(AUTOCONF) {
+            Some(autoconf) if !autoconf.is_empty() => parse_autoconf(autoconf)?,
+            _ => Autoconf::Dhcp,
+        };
+
+        Ok(Self { device, autoconf })
+    }
+}
+
+fn parse_autoconf(value: &str) -> Result<Autoconf> {
+    let autoconf = match value {
+        "off" | "none" => Autoconf::Off,
+        // DHCP is the only supported protocol, so it is used for any protocol.
+        "on" | "any" | "dhcp" => Autoconf::Dhcp,
+        "bootp" | "rarp" => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the protocol is not supported")
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the protocol is invalid"),
+    };
+
+    Ok(autoconf)
+}
@@ -0,0 +1,408 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The DHCP messages and the frames that carry them.
+//!
+//! Before the iface has an address, the messages cannot go through the network stack, so they
+//! are sent and received in raw frames:
+//!
+//! ```text
+//! | Ethernet (14) | IPv4 (20) | UDP (8) | DHCP message |
+//! ```
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc2131>,
+//! <https://datatracker.ietf.org/doc/html/rfc2132>.
+
+use aster_bigtcp::wire::{EthernetAddress, Ipv4Address};
+
+use crate::prelude::*;
+
+pub(super) const DHCP_SERVER_PORT: u16 = 67;
+pub(super) const DHCP_CLIENT_PORT: u16 = 68;
+
+const ETH_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const ETH_P_IP: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+/// The TTL of the messages, which is the same as Linux.
+const DHCP_TTL: u8 = 64;
+
+/// The length of the fixed part of the DHCP message, including the magic cookie.
+const FIXED_LEN: usize = 240;
+/// The minimum length of the DHCP message, which is required by some BOOTP relay agents.
+const MIN_MESSAGE_LEN: usize = 300;
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+/// The flag that asks the server to broadcast the replies.
+///
+/// The client cannot receive unicast replies before it has an address in some networks.
+const FLAG_BROADCAST: u16 = 0x8000;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS_SERVER: u8 = 6;
+const OPT_DOMAIN_NAME: u8 = 15;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPT_MAX_MESSAGE_SIZE: u8 = 57;
+const OPT_RENEWAL_TIME: u8 = 58;
+const OPT_REBINDING_TIME: u8 = 59;
+const OPT_CLIENT_ID: u8 = 61;
+const OPT_END: u8 = 255;
+
+/// The maximum size of the DHCP messages that the client accepts.
+const MAX_MESSAGE_SIZE: u16 = 1500 - (IPV4_HEADER_LEN + UDP_HEADER_LEN) as u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u8)]
+pub(super) enum MessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+    Inform = 8,
+}
+
+/// A DHCP message sent by the client.
+#[derive(Debug)]
+pub(super) struct ClientMessage {
+    pub(super) message_type: MessageType,
+    pub(super) xid: u32,
+    pub(super) chaddr: EthernetAddress,
+    /// The address of the client, which is only known when the lease is renewed.
+    pub(super) ciaddr: Ipv4Address,
+    pub(super) requested_ip: Option<Ipv4Address>,
+    pub(super) server_id: Option<Ipv4Address>,
+}
+
+impl ClientMessage {
+    pub(super) fn to_bytes(&self) -> Vec<u8> {
+        let mut message = vec![0u8; FIXED_LEN];
+        message[0] = BOOTREQUEST;
+        message[1] = HTYPE_ETHERNET;
+        message[2] = EthernetAddress::SIZE as u8;
+        message[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        // Only the client without an address needs the replies to be broadcast.
+        if self.ciaddr.is_unspecified() {
+            message[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+        }
+        message[12..16].copy_from_slice(&self.ciaddr.octets());
+        message[28..34].copy_from_slice(self.chaddr.as_bytes());
+        message[236..240].copy_from_slice(&MAGIC_COOKIE);
+
+        message.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, self.message_type as u8]);
+        if let Some(requested_ip) = self.requested_ip {
+            message.extend_from_slice(&[OPT_REQUESTED_IP, 4]);
+            message.extend_from_slice(&requested_ip.octets());
+        }
+        if let Some(server_id) = self.server_id {
+            message.extend_from_slice(&[OPT_SERVER_ID, 4]);
+            message.extend_from_slice(&server_id.octets());
+        }
+        message.extend_from_slice(&[OPT_CLIENT_ID, 1 + EthernetAddress::SIZE as u8]);
+        message.push(HTYPE_ETHERNET);
+        message.extend_from_slice(self.chaddr.as_bytes());
+        message.extend_from_slice(&[OPT_MAX_MESSAGE_SIZE, 2]);
+        message.extend_from_slice(&MAX_MESSAGE_SIZE.to_be_bytes());
+        message.extend_from_slice(&[
+            OPT_PARAMETER_REQUEST_LIST,
+            6,
+            OPT_SUBNET_MASK,
+            OPT_ROUTER,
+            OPT_DNS_SERVER,
+            OPT_DOMAIN_NAME,
+            OPT_RENEWAL_TIME,
+            OPT_REBINDING_TIME,
+        ]);
+        message.push(OPT_END);
+
+        if message.len() < MIN_MESSAGE_LEN {
+            message.resize(MIN_MESSAGE_LEN, OPT_PAD);
+        }
+
+        message
+    }
+}
+
+/// A DHCP message sent by the server.
+#[derive(Debug, Default)]
+pub(super) struct ServerMessage {
+    pub(super) message_type: Option<MessageType>,
+    pub(super) xid: u32,
+    pub(super) chaddr: [u8; EthernetAddress::SIZE],
+    /// The address offered to the client.
+    pub(super) yiaddr: Option<Ipv4Address>,
+    /// The address of the next server to use in bootstrap.
+    pub(super) siaddr: Option<Ipv4Address>,
+    pub(super) server_id: Option<Ipv4Address>,
+    pub(super) subnet_mask: Option<Ipv4Address>,
+    pub(super) routers: Vec<Ipv4Address>,
+    pub(super) dns_servers: Vec<Ipv4Address>,
+    pub(super) domain_name: Option<String>,
+    /// The lease time in seconds, where `u32::MAX` means infinity.
+    pub(super) lease_time: Option<u32>,
+    pub(super) renewal_time: Option<u32>,
+    pub(super) rebinding_time: Option<u32>,
+}
+
+impl ServerMessage {
+    /// Parses a DHCP message sent by the server.
+    ///
+    /// Returns `None` if the message is ill-formed or is not a reply.
+    pub(super) fn parse(message: &[u8]) -> Option<Self> {
+        if message.len() < FIXED_LEN
+            || message[0] != BOOTREPLY
+            || message[1] != HTYPE_ETHERNET
+            || message[2] != EthernetAddress::SIZE as u8
+            || message[236..240] != MAGIC_COOKIE
+        {
+            return None;
+        }
+
+        let mut res = Self {
+            xid: u32::from_be_bytes(message[4..8].try_into().unwrap()),
+            chaddr: message[28..34].try_into().unwrap(),
+            yiaddr: read_addr(&message[16..20]).filter(|addr| !addr.is_unspecified()),
+            siaddr: read_addr(&message[20..24]).filter(|addr| !addr.is_unspecified()),
+            ..Default::default()
+        };
+
+        // FIXME: The options in the `sname` and `file` fields (i.e., option overloading) are
+        // ignored.
+        let mut options = &message[FIXED_LEN..];
+        while let Some((&code, rest)) = options.split_first() {
+            match code {
+                OPT_PAD => {
+                    options = rest;
+                    continue;
+                }
+                OPT_END => break,
+                _ => (),
+            }
+
+            let (&len, rest) = rest.split_first()?;
+            let value = rest.get(..len as usize)?;
+            options = &rest[len as usize..];
+
+            match code {
+                OPT_MESSAGE_TYPE => {
+                    res.message_type = value.first().and_then(|ty| MessageType::try_from(*ty).ok())
+                }
+                OPT_SERVER_ID => res.server_id = read_addr(value),
+                OPT_SUBNET_MASK => res.subnet_mask = read_addr(value),
+                OPT_ROUTER => res.routers = value.chunks_exact(4).filter_map(read_addr).collect(),
+                OPT_DNS_SERVER => {
+                    res.dns_servers = value.chunks_exact(4).filter_map(read_addr).collect()
+                }
+                OPT_DOMAIN_NAME => {
+                    // Some servers terminate the name with nul characters.
+                    let name = core::str::from_utf8(value).ok()?.trim_end_matches('\0');
+                    res.domain_name = Some(name.to_string()).filter(|name| !name.is_empty());
+                }
+                OPT_LEASE_TIME => res.lease_time = read_u32(value),
+                OPT_RENEWAL_TIME => res.renewal_time = read_u32(value),
+                OPT_REBINDING_TIME => res.rebinding_time = read_u32(value),
+                _ => (),
+            }
+        }
+
+        Some(res)
+    }
+}
+
+fn read_addr(value: &[u8]) -> Option<Ipv4Address> {
+    let octets: [u8; 4] = value.try_into().ok()?;
+    Some(Ipv4Address::from(octets))
+}
+
+fn read_u32(value: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(value.try_into().ok()?))
+}
+
+/// Builds a broadcast frame that carries the DHCP message from a client without an address.
+pub(super) fn build_frame(src_mac: EthernetAddress, message: &[u8]) -> Vec<u8> {
+    let udp_len = UDP_HEADER_LEN + message.len();
+    let ip_len = IPV4_HEADER_LEN + udp_len;
+    let mut frame = vec![0u8; ETH_HEADER_LEN + ip_len];
+
+    let eth = &mut frame[..ETH_HEADER_LEN];
+    eth[0..6].copy_from_slice(EthernetAddress::BROADCAST.as_bytes());
+    eth[6..12].copy_from_slice(src_mac.as_bytes());
+    eth[12..14].copy_from_slice(&ETH_P_IP.to_be_bytes());
+
+    let ip = &mut frame[ETH_HEADER_LEN..ETH_HEADER_LEN + IPV4_HEADER_LEN];
+    ip[0] = 0x45;
+    ip[2..4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+    ip[8] = DHCP_TTL;
+    ip[9] = IPPROTO_UDP;
+    ip[12..16].copy_from_slice(&Ipv4Address::UNSPECIFIED.octets());
+    ip[16..20].copy_from_slice(&Ipv4Address::BROADCAST.octets());
+    let checksum = ipv4_checksum(ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    // The UDP checksum is optional for IPv4, so it is left as zero.
+    let udp_offset = ETH_HEADER_LEN + IPV4_HEADER_LEN;
+    let udp = &mut frame[udp_offset..udp_offset + UDP_HEADER_LEN];
+    udp[0..2].copy_from_slice(&DHCP_CLIENT_PORT.to_be_bytes());
+    udp[2..4].copy_from_slice(&DHCP_SERVER_PORT.to_be_bytes());
+    udp[4..6].copy_from_slice(&(udp_len as u16).to_be_bytes());
+
+    frame[udp_offset + UDP_HEADER_LEN..].copy_from_slice(message);
+
+    frame
+}
+
+/// Extracts the DHCP message from a frame that is sent to the client.
+///
+/// Returns `None` if the frame does not carry a UDP datagram to the DHCP client port.
+pub(super) fn parse_frame(frame: &[u8]) -> Option<&[u8]> {
+    let eth = frame.get(..ETH_HEADER_LEN)?;
+    if eth[12..14] != ETH_P_IP.to_be_bytes() {
+        return None;
+    }
+
+    let packet = &frame[ETH_HEADER_LEN..];
+    let ip = packet.get(..IPV4_HEADER_LEN)?;
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    // The fragments are not reassembled, since the replies are small enough.
+    let is_fragment = u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff != 0;
+    if ip[0] >> 4 != 4
+        || ip[9] != IPPROTO_UDP
+        || is_fragment
+        || header_len < IPV4_HEADER_LEN
+        || total_len < header_len + UDP_HEADER_LEN
+        || total_len > packet.len()
+    {
+        return None;
+    }
+
+    let udp = &packet[header_len..total_len];
+    let udp_len = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+    if udp[2..4] != DHCP_CLIENT_PORT.to_be_bytes()
+        || udp_len < UDP_HEADER_LEN
+        || udp_len > udp.len()
+    {
+        return None;
+    }
+
+    Some(&udp[UDP_HEADER_LEN..udp_len])
+}
+
+/// Computes the Internet checksum of the IPv4 header.
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    const CLIENT_MAC: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+
+    #[ktest]
+    fn discover_frame() {
+        let message = ClientMessage {
+            message_type: MessageType::Discover,
+            xid: 0x12345678,
+            chaddr: CLIENT_MAC,
+            ciaddr: Ipv4Address::UNSPECIFIED,
+            requested_ip: None,
+            server_id: None,
+        }
+        .to_bytes();
+        assert_eq!(message.len(), MIN_MESSAGE_LEN);
+        assert_eq!(&message[4..8], &[0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(&message[10..12], &[0x80, 0x00]);
+        assert_eq!(&message[28..34], CLIENT_MAC.as_bytes());
+        assert_eq!(&message[240..243], &[OPT_MESSAGE_TYPE, 1, 1]);
+
+        let frame = build_frame(CLIENT_MAC, &message);
+        assert_eq!(&frame[0..6], &[0xff; 6]);
+        assert_eq!(&frame[6..12], CLIENT_MAC.as_bytes());
+
+        let ip = &frame[ETH_HEADER_LEN..ETH_HEADER_LEN + IPV4_HEADER_LEN];
+        // The checksum of a header with a valid checksum is zero.
+        assert_eq!(ipv4_checksum(ip), 0);
+        assert_eq!(&ip[16..20], &[0xff; 4]);
+
+        // The frame is sent to the server port, so the client does not accept it.
+        assert_eq!(parse_frame(&frame), None);
+    }
+
+    #[ktest]
+    fn parse_ack() {
+        let mut message = vec![0u8; FIXED_LEN];
+        message[0] = BOOTREPLY;
+        message[1] = HTYPE_ETHERNET;
+        message[2] = 6;
+        message[4..8].copy_from_slice(&[0xaa, 0xbb, 0xcc, 0xdd]);
+        message[16..20].copy_from_slice(&[10, 0, 2, 15]);
+        message[28..34].copy_from_slice(CLIENT_MAC.as_bytes());
+        message[236..240].copy_from_slice(&MAGIC_COOKIE);
+        message.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, 5]);
+        message.extend_from_slice(&[OPT_SERVER_ID, 4, 10, 0, 2, 2]);
+        message.extend_from_slice(&[OPT_PAD, OPT_SUBNET_MASK, 4, 255, 255, 255, 0]);
+        message.extend_from_slice(&[OPT_ROUTER, 4, 10, 0, 2, 2]);
+        message.extend_from_slice(&[OPT_DNS_SERVER, 8, 10, 0, 2, 3, 8, 8, 8, 8]);
+        message.extend_from_slice(&[OPT_DOMAIN_NAME, 5, b'l', b'o', b'c', b'a', 0]);
+        message.extend_from_slice(&[OPT_LEASE_TIME, 4, 0, 0, 0x0e, 0x10]);
+        message.extend_from_slice(&[OPT_END, 0xde, 0xad]);
+
+        // Wrap the message in a frame with an IP option to check the header length.
+        let mut frame = build_frame(CLIENT_MAC, &message);
+        let udp_offset = ETH_HEADER_LEN + IPV4_HEADER_LEN;
+        frame[udp_offset..udp_offset + 2].copy_from_slice(&DHCP_SERVER_PORT.to_be_bytes());
+        frame[udp_offset + 2..udp_offset + 4].copy_from_slice(&DHCP_CLIENT_PORT.to_be_bytes());
+        frame.splice(udp_offset..udp_offset, [1, 1, 1, 0]);
+        frame[ETH_HEADER_LEN] = 0x46;
+        let ip_len = (frame.len() - ETH_HEADER_LEN) as u16;
+        frame[ETH_HEADER_LEN + 2..ETH_HEADER_LEN + 4].copy_from_slice(&ip_len.to_be_bytes());
+        // The padding of the frame is not a part of the message.
+        frame.extend_from_slice(&[0; 4]);
+
+        let ack = ServerMessage::parse(parse_frame(&frame).unwrap()).unwrap();
+        assert_eq!(ack.message_type, Some(MessageType::Ack));
+        assert_eq!(ack.xid, 0xaabbccdd);
+        assert_eq!(ack.chaddr, CLIENT_MAC.0);
+        assert_eq!(ack.yiaddr, Some(Ipv4Address::new(10, 0, 2, 15)));
+        assert_eq!(ack.siaddr, None);
+        assert_eq!(ack.server_id, Some(Ipv4Address::new(10, 0, 2, 2)));
+        assert_eq!(ack.subnet_mask, Some(Ipv4Address::new(255, 255, 255, 0)));
+        assert_eq!(ack.routers, [Ipv4Address::new(10, 0, 2, 2)]);
+        assert_eq!(
+            ack.dns_servers,
+            [Ipv4Address::new(10, 0, 2, 3), Ipv4Address::new(8, 8, 8, 8)]
+        );
+        assert_eq!(ack.domain_name.as_deref(), Some("loca"));
+        assert_eq!(ack.lease_time, Some(3600));
+        assert_eq!(ack.renewal_time, None);
+
+        // A request is not a reply.
+        message[0] = BOOTREQUEST;
+        assert!(ServerMessage::parse(&message).is_none());
+        // A truncated option makes the message ill-formed.
+        message[0] = BOOTREPLY;
+        message.truncate(FIXED_LEN + 5);
+        assert!(ServerMessage::parse(&message).is_none());
+    }
+}
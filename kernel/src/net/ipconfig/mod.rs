@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! IP autoconfiguration at boot time.
+//!
+//! If the kernel command line contains `ip=dhcp`, the kernel acquires a lease with DHCP before
+//! the init process is started, and configures the address, the default route, and the DNS
+//! servers of an Ethernet iface accordingly. This allows the root file system or the init process
+//! to use the network without a DHCP client in user space, e.g., when the kernel is booted from
+//! the network.
+//!
+//! A kernel thread renews the lease until it expires. The result is reported in `/proc/net/pnp`,
+//! whose format is the same as Linux. So `/etc/resolv.conf` can be a symbolic link to it.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/ipconfig.c>.
+
+mod client;
+mod config;
+mod message;
+
+use aster_bigtcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+use aster_time::read_monotonic_time;
+use ostd::{boot::boot_info, sync::WaitQueue};
+
+use self::{
+    client::Lease,
+    config::{Autoconf, IpConfig},
+};
+use crate::{
+    kcmdline::KCmdlineArg,
+    net::{
+        iface::{iter_all_ifaces, virtio_iface, Iface},
+        route::{self, FibRoute, RouteType, RTPROT_DHCP, TABLE_MAIN},
+    },
+    prelude::*,
+    thread::kernel_thread::ThreadOptions,
+    WaitTimeout,
+};
+
+/// The lease that configures the iface, if any.
+static LEASE: Mutex<Option<Lease>> = Mutex::new(None);
+
+/// Configures the iface according to the `ip` argument of the kernel command line.
+///
+/// This should be called after the ifaces are polled in the background and before the init
+/// process is started.
+pub fn lazy_init() {
+    let karg = KCmdlineArg::from(boot_info().kernel_cmdline.as_str());
+    let Some(value) = karg.get_ip_config() else {
+        return;
+    };
+
+    let config = match IpConfig::parse(value) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("[ipconfig] invalid argument `ip={}`: {:?}", value, err);
+            return;
+        }
+    };
+    if config.autoconf == Autoconf::Off {
+        return;
+    }
+
+    let Some(iface) = find_iface(config.device.as_deref()) else {
+        warn!("[ipconfig] no Ethernet interface is available");
+        return;
+    };
+
+    // The static configuration of the iface is replaced by the lease.
+    for cidr in iface.ipv4_addrs() {
+        iface.remove_ip_addr(IpCidr::Ipv4(cidr));
+    }
+    iface.remove_route(&default_route_cidr());
+
+    println!(
+        "[kernel] IP-Config: Sending DHCP requests via {}",
+        iface.name()
+    );
+    let lease = match client::acquire(&iface) {
+        Ok(lease) => lease,
+        Err(err) => {
+            println!(
+                "[kernel] IP-Config: Auto-configuration of network failed: {:?}",
+                err
+            );
+            return;
+        }
+    };
+    apply_lease(&iface, &lease);
+    println!(
+        "[kernel] IP-Config: Got DHCP answer from {}, my address is {}",
+        lease.server, lease.addr
+    );
+
+    if lease.lease_time.is_some() {
+        ThreadOptions::new(move || maintain_lease(iface, lease)).spawn();
+    }
+}
+
+/// Finds the Ethernet iface with the name, or `eth0` if the name is not specified.
+fn find_iface(name: Option<&str>) -> Option<Arc<Iface>> {
+    let Some(name) = name else {
+        return virtio_iface().cloned();
+    };
+
+    iter_all_ifaces().find(|iface| iface.name() == name && iface.ether_addr().is_some())
+}
+
+fn default_route_cidr() -> IpCidr {
+    IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0))
+}
+
+fn apply_lease(iface: &Arc<Iface>, lease: &Lease) {
+    if !iface.add_ipv4_addr(lease.addr) {
+        warn!("[ipconfig] the interface has too many addresses");
+    }
+
+    if let Some(router) = lease.router {
+        let route = default_route(iface, router);
+        let _ = route::modify_fib(|fib| {
+            fib.table_mut(TABLE_MAIN).routes_mut().push(route);
+            Ok(())
+        });
+    }
+
+    *LEASE.lock() = Some(lease.clone());
+}
+
+fn remove_lease(iface: &Arc<Iface>, lease: &Lease) {
+    iface.remove_ip_addr(IpCidr::Ipv4(lease.addr));
+    if let Some(router) = lease.router {
+        let route = default_route(iface, router);
+        let _ = route::modify_fib(|fib| {
+            fib.table_mut(TABLE_MAIN)
+                .routes_mut()
+                .retain(|other| *other != route);
+            Ok(())
+        });
+    }
+
+    *LEASE.lock() = None;
+}
+
+/// Returns the default route via the router that is offered by the DHCP server.
+fn default_route(iface: &Iface, router: Ipv4Address) -> FibRoute {
+    FibRoute {
+        dst: default_route_cidr(),
+        type_: RouteType::Unicast,
+        gateway: Some(IpAddress::Ipv4(router)),
+        oif: Some(iface.index()),
+        pref_src: None,
+        metric: 0,
+        protocol: RTPROT_DHCP,
+    }
+}
+
+/// Renews the lease before it expires, and acquires a new lease after it expires.
+///
+/// The kernel thread exits if the lease cannot be maintained, e.g., if a DHCP client in user
+/// space takes over the DHCP client port.
+fn maintain_lease(iface: Arc<Iface>, mut lease: Lease) {
+    let wait_queue = WaitQueue::new();
+
+    loop {
+        let now = read_monotonic_time();
+        let renew_at = lease.renew_at();
+        if now < renew_at {
+            let _ = wait_queue.wait_until_or_timeout(|| None::<()>, &(renew_at - now));
+        }
+
+        let new_lease = match client::renew(&iface, &lease) {
+            Ok(Some(new_lease)) => new_lease,
+            Ok(None) => {
+                warn!("[ipconfig] the lease of {} is lost", lease.addr);
+                remove_lease(&iface, &lease);
+                match client::acquire(&iface) {
+                    Ok(new_lease) => {
+                        apply_lease(&iface, &new_lease);
+                        lease = new_lease;
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!("[ipconfig] failed to acquire a new lease: {:?}", err);
+                        return;
+                    }
+                }
+            }
+            Err(err) => {
+                warn!("[ipconfig] failed to renew the lease: {:?}", err);
+                return;
+            }
+        };
+
+        if new_lease.addr != lease.addr || new_lease.router != lease.router {
+            remove_lease(&iface, &lease);
+            apply_lease(&iface, &new_lease);
+        } else {
+            *LEASE.lock() = Some(new_lease.clone());
+        }
+        lease = new_lease;
+
+        if lease.lease_time.is_none() {
+            return;
+        }
+    }
+}
+
+/// Returns the content of `/proc/net/pnp`.
+pub fn pnp_report() -> String {
+    let Some(lease) = LEASE.lock().clone() else {
+        return "#MANUAL\n".to_string();
+    };
+
+    let mut report = "#PROTO: DHCP\n".to_string();
+    if let Some(domain_name) = lease.domain_name.as_ref() {
+        report.push_str(&format!("domain {}\n", domain_name));
+    }
+    for dns_server in lease.dns_servers.iter() {
+        report.push_str(&format!("nameserver {}\n", dns_server));
+    }
+    report.push_str(&format!("bootserver {}\n", lease.server));
+
+    report
+}
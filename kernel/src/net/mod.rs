@@ -1,9 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub mod iface;
+pub mod ipconfig;
+pub mod netfilter;
+pub mod pktgen;
+pub mod route;
 pub mod socket;
 
 pub fn init() {
+    aster_bigtcp::socket::set_syn_cookie_random_source(crate::util::random::getrandom);
+    route::init();
     iface::init();
     socket::netlink::init();
     socket::vsock::init();
@@ -12,4 +18,5 @@ pub fn init() {
 /// Lazy init should be called after spawning init thread.
 pub fn lazy_init() {
     iface::lazy_init();
+    ipconfig::lazy_init();
 }
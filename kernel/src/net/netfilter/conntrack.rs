@@ -0,0 +1,674 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Connection tracking.
+//!
+//! Connections are identified by their tuples (i.e., the addresses, the transport protocol, and
+//! the ports). The tuple in the reply direction is the reversed tuple in the original direction,
+//! unless the connection is translated by NAT (see [`super::nat`]). A connection is created when
+//! the first packet of it is accepted by the filter, and it expires after being idle for a while.
+//! The states of TCP connections are also tracked, so closing connections expire sooner.
+
+use core::time::Duration;
+
+use aster_softirq::BottomHalfDisabled;
+use aster_time::read_monotonic_time;
+
+use super::{
+    nat::{self, ManipType, NatRange},
+    packet::PacketInfo,
+    ruleset::NfProto,
+};
+use crate::prelude::*;
+
+bitflags! {
+    /// The connection tracking state of a packet.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_conntrack_common.h>.
+    pub struct CtState: u32 {
+        const INVALID     = 1 << 0;
+        const ESTABLISHED = 1 << 1;
+        const RELATED     = 1 << 2;
+        const NEW         = 1 << 3;
+        const UNTRACKED   = 1 << 6;
+    }
+}
+
+/// The tracking information of a packet.
+pub(super) struct CtInfo {
+    state: CtState,
+    /// The connection of the packet.
+    ///
+    /// This is `None` if the packet should not create or refresh connections.
+    conn: Option<ConnInfo>,
+}
+
+/// The connection that a packet belongs to.
+struct ConnInfo {
+    /// The tuple in the original direction, which identifies the connection.
+    original: Tuple,
+    /// The tuple in the reply direction.
+    reply: Tuple,
+    /// Whether the packet is in the reply direction.
+    is_reply: bool,
+    update: Update,
+}
+
+/// How a packet updates its connection after being accepted.
+enum Update {
+    /// The packet creates a new connection.
+    New {
+        tcp_state: Option<TcpState>,
+        nat_done: NatDone,
+    },
+    /// The packet refreshes the connection, and moves it to a new state if it is a TCP
+    /// connection.
+    Refresh(Option<TcpState>),
+    /// The packet does not affect the connection (e.g., a retransmitted SYN segment).
+    Ignore,
+}
+
+bitflags! {
+    /// The NAT bindings that have been set up for a new connection.
+    struct NatDone: u8 {
+        const SRC = 1 << 0;
+        const DST = 1 << 1;
+    }
+}
+
+impl NatDone {
+    fn of(manip: ManipType) -> Self {
+        match manip {
+            ManipType::Src => Self::SRC,
+            ManipType::Dst => Self::DST,
+        }
+    }
+}
+
+impl CtInfo {
+    fn without_conn(state: CtState) -> Self {
+        Self { state, conn: None }
+    }
+
+    fn new_conn(tuple: Tuple, segment: Option<TcpSegment>) -> Self {
+        let tcp_state = match segment.map(TcpState::start) {
+            None => None,
+            Some(Some(tcp_state)) => Some(tcp_state),
+            Some(None) => return Self::without_conn(CtState::INVALID),
+        };
+
+        Self {
+            state: CtState::NEW,
+            conn: Some(ConnInfo {
+                original: tuple,
+                reply: tuple.reversed(),
+                is_reply: false,
+                update: Update::New {
+                    tcp_state,
+                    nat_done: NatDone::empty(),
+                },
+            }),
+        }
+    }
+
+    pub(super) fn state(&self) -> CtState {
+        self.state
+    }
+
+    /// Returns whether the NAT binding of a manipulation type needs to be set up.
+    ///
+    /// Like Linux, the NAT bindings are set up by the first packet of a connection. The
+    /// following packets are translated according to the bindings.
+    pub(super) fn needs_nat_binding(&self, manip: ManipType) -> bool {
+        match self.conn.as_ref() {
+            Some(ConnInfo {
+                update: Update::New { nat_done, .. },
+                ..
+            }) => !nat_done.contains(NatDone::of(manip)),
+            _ => false,
+        }
+    }
+
+    /// Sets up the NAT binding of a manipulation type for a new connection.
+    ///
+    /// If `range` is `None`, the connection is not translated unless its tuples clash with
+    /// another connection, in which case the source port may be changed.
+    ///
+    /// Returns `false` if no unique tuples can be found for the connection.
+    pub(super) fn bind_nat(&mut self, manip: ManipType, range: Option<&NatRange>) -> bool {
+        let Some(ConnInfo {
+            reply,
+            update: Update::New { nat_done, .. },
+            ..
+        }) = self.conn.as_mut()
+        else {
+            return true;
+        };
+        nat_done.insert(NatDone::of(manip));
+
+        let now = read_monotonic_time();
+        let table = CONNTRACK.lock();
+
+        // The tuple of the packets in the original direction after being translated.
+        let mut translated = reply.reversed();
+        let is_unique = nat::choose_tuple(&mut translated, manip, range, |tuple| {
+            table.lookup(&tuple.reversed(), now).is_some()
+        });
+        *reply = translated.reversed();
+
+        is_unique
+    }
+
+    /// Translates a packet according to the NAT bindings of its connection.
+    pub(super) fn nat_packet(&self, manip: ManipType, packet: &mut [u8]) {
+        let Some(conn) = self.conn.as_ref() else {
+            return;
+        };
+
+        // After being translated, the packet should match the reversed tuple in the other
+        // direction.
+        let translated = if conn.is_reply {
+            conn.original.reversed()
+        } else {
+            conn.reply.reversed()
+        };
+        nat::manip_packet(packet, manip, &translated);
+    }
+}
+
+/// Looks up the connection of a packet.
+pub(super) fn track(pkt: &PacketInfo) -> CtInfo {
+    let Some(transport) = pkt.transport() else {
+        // TODO: Track the fragments after they are reassembled.
+        return CtInfo::without_conn(CtState::UNTRACKED);
+    };
+
+    match classify_icmp(pkt.nfproto(), pkt.l4proto(), transport) {
+        Some(IcmpKind::Error(inner)) => {
+            // TODO: Translate the packets embedded in the ICMP errors if the connections are
+            // translated by NAT.
+            let now = read_monotonic_time();
+            let state = match inner
+                .and_then(|inner| Tuple::from_packet(&inner))
+                .filter(|tuple| CONNTRACK.lock().lookup(tuple, now).is_some())
+            {
+                Some(_) => CtState::RELATED,
+                None => CtState::INVALID,
+            };
+            return CtInfo::without_conn(state);
+        }
+        Some(IcmpKind::Other) => {
+            // Like Linux, other ICMP messages (e.g., neighbor discovery messages) are not
+            // tracked.
+            return CtInfo::without_conn(CtState::UNTRACKED);
+        }
+        Some(IcmpKind::Echo) | None => (),
+    }
+
+    let Some(tuple) = Tuple::from_packet(pkt) else {
+        return CtInfo::without_conn(CtState::INVALID);
+    };
+    let segment = if tuple.l4proto == IPPROTO_TCP {
+        match TcpSegment::classify(transport) {
+            Some(segment) => Some(segment),
+            None => return CtInfo::without_conn(CtState::INVALID),
+        }
+    } else {
+        None
+    };
+
+    let now = read_monotonic_time();
+    let mut table = CONNTRACK.lock();
+
+    let Some((original, conn, is_reply)) = table.lookup(&tuple, now) else {
+        drop(table);
+        return CtInfo::new_conn(tuple, segment);
+    };
+
+    let update = match (segment, conn.tcp_state) {
+        (Some(segment), Some(tcp_state)) => match tcp_state.transit(segment, is_reply) {
+            TcpTransition::To(new_state) => Update::Refresh(Some(new_state)),
+            TcpTransition::Ignore => Update::Ignore,
+            TcpTransition::Invalid => return CtInfo::without_conn(CtState::INVALID),
+            TcpTransition::Reopen => {
+                // Like Linux, the closed connection is removed so that a new connection can be
+                // created with the same tuple.
+                table.remove(&original);
+                drop(table);
+                return CtInfo::new_conn(tuple, segment);
+            }
+        },
+        (_, _) => Update::Refresh(None),
+    };
+
+    let state = if is_reply || conn.is_replied {
+        CtState::ESTABLISHED
+    } else {
+        CtState::NEW
+    };
+    CtInfo {
+        state,
+        conn: Some(ConnInfo {
+            original,
+            reply: conn.reply,
+            is_reply,
+            update,
+        }),
+    }
+}
+
+/// Creates or refreshes the connection of a packet that has been accepted.
+///
+/// Returns `false` if the packet should be dropped because its connection cannot be created.
+pub(super) fn confirm(info: CtInfo) -> bool {
+    let Some(conn_info) = info.conn else {
+        return true;
+    };
+
+    let now = read_monotonic_time();
+    let mut table = CONNTRACK.lock();
+
+    match conn_info.update {
+        Update::New { tcp_state, .. } => table.insert(
+            conn_info.original,
+            Conn {
+                reply: conn_info.reply,
+                is_replied: false,
+                tcp_state,
+                expires_at: now + timeout(conn_info.original.l4proto, false, tcp_state),
+            },
+            now,
+        ),
+        Update::Refresh(tcp_state) => {
+            let Some(conn) = table.conns.get_mut(&conn_info.original) else {
+                // The connection has been removed since the packet was tracked.
+                return true;
+            };
+            conn.is_replied |= conn_info.is_reply;
+            if tcp_state.is_some() {
+                conn.tcp_state = tcp_state;
+            }
+            conn.expires_at =
+                now + timeout(conn_info.original.l4proto, conn.is_replied, conn.tcp_state);
+            true
+        }
+        Update::Ignore => true,
+    }
+}
+
+static CONNTRACK: SpinLock<ConnTable, BottomHalfDisabled> = SpinLock::new(ConnTable {
+    conns: BTreeMap::new(),
+    replies: BTreeMap::new(),
+});
+
+/// The maximum number of tracked connections.
+const MAX_CONNS: usize = 65536;
+
+/// Returns how long a connection can stay idle.
+///
+/// Reference: <https://docs.kernel.org/networking/nf_conntrack-sysctl.html>.
+fn timeout(l4proto: u8, is_replied: bool, tcp_state: Option<TcpState>) -> Duration {
+    if let Some(tcp_state) = tcp_state {
+        return tcp_state.timeout();
+    }
+
+    let secs = match (l4proto, is_replied) {
+        (IPPROTO_UDP, false) => 30,
+        (IPPROTO_UDP, true) => 120,
+        (IPPROTO_ICMP | IPPROTO_ICMPV6, _) => 30,
+        (_, _) => 600,
+    };
+    Duration::from_secs(secs)
+}
+
+pub(super) const IPPROTO_ICMP: u8 = 1;
+pub(super) const IPPROTO_TCP: u8 = 6;
+pub(super) const IPPROTO_UDP: u8 = 17;
+pub(super) const IPPROTO_ICMPV6: u8 = 58;
+
+struct ConnTable {
+    /// The connections indexed by their tuples in the original direction.
+    conns: BTreeMap<Tuple, Conn>,
+    /// The tuples in the original direction indexed by the tuples in the reply direction.
+    replies: BTreeMap<Tuple, Tuple>,
+}
+
+struct Conn {
+    reply: Tuple,
+    is_replied: bool,
+    /// The TCP state, which is `None` if the connection is not a TCP connection.
+    tcp_state: Option<TcpState>,
+    expires_at: Duration,
+}
+
+impl ConnTable {
+    /// Looks up the live connection that a tuple belongs to.
+    ///
+    /// Returns the tuple of the connection in the original direction, the connection, and
+    /// whether the tuple is in the reply direction.
+    fn lookup(&self, tuple: &Tuple, now: Duration) -> Option<(Tuple, &Conn, bool)> {
+        if let Some(conn) = self.conns.get(tuple) {
+            return (conn.expires_at > now).then_some((*tuple, conn, false));
+        }
+
+        let original = self.replies.get(tuple)?;
+        self.conns
+            .get(original)
+            .filter(|conn| conn.expires_at > now)
+            .map(|conn| (*original, conn, true))
+    }
+
+    /// Inserts a new connection.
+    ///
+    /// Returns `false` if the table is full or the tuples clash with another connection.
+    fn insert(&mut self, original: Tuple, conn: Conn, now: Duration) -> bool {
+        // The connections may be created concurrently, so the tuples must be checked again.
+        if self.lookup(&original, now).is_some() || self.lookup(&conn.reply, now).is_some() {
+            return false;
+        }
+        self.remove_stale(&original);
+        self.remove_stale(&conn.reply);
+
+        if self.conns.len() >= MAX_CONNS {
+            self.remove_expired(now);
+            if self.conns.len() >= MAX_CONNS {
+                // Like Linux, the packet is dropped if the table is full.
+                return false;
+            }
+        }
+
+        self.replies.insert(conn.reply, original);
+        self.conns.insert(original, conn);
+        true
+    }
+
+    /// Removes a connection with its tuple in the original direction.
+    fn remove(&mut self, original: &Tuple) {
+        if let Some(conn) = self.conns.remove(original) {
+            self.replies.remove(&conn.reply);
+        }
+    }
+
+    /// Removes the expired connection that uses a tuple, if any.
+    fn remove_stale(&mut self, tuple: &Tuple) {
+        if self.conns.contains_key(tuple) {
+            self.remove(tuple);
+        }
+        if let Some(original) = self.replies.get(tuple).copied() {
+            self.remove(&original);
+        }
+    }
+
+    fn remove_expired(&mut self, now: Duration) {
+        self.conns.retain(|_, conn| conn.expires_at > now);
+
+        let conns = &self.conns;
+        self.replies
+            .retain(|_, original| conns.contains_key(original));
+    }
+}
+
+/// The state of a TCP connection.
+///
+/// Unlike Linux, the sequence numbers are not tracked, and the simultaneous opening is not
+/// supported.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netfilter/nf_conntrack_proto_tcp.c>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+    SynSent,
+    SynRecv,
+    Established,
+    FinWait,
+    CloseWait,
+    LastAck,
+    TimeWait,
+    Close,
+}
+
+/// The kind of a TCP segment, which determines the state transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpSegment {
+    Syn,
+    SynAck,
+    Fin,
+    Ack,
+    Rst,
+    None,
+}
+
+/// The result of a TCP segment on the state of the connection.
+enum TcpTransition {
+    /// The connection moves to a new state.
+    To(TcpState),
+    /// The segment is accepted, but the connection is not affected (e.g., retransmissions).
+    Ignore,
+    /// The segment is invalid for the connection.
+    Invalid,
+    /// The segment opens a new connection that replaces the closed connection.
+    Reopen,
+}
+
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_SYN: u8 = 0x02;
+const TCP_FLAG_RST: u8 = 0x04;
+const TCP_FLAG_ACK: u8 = 0x10;
+
+impl TcpSegment {
+    /// Classifies a TCP segment by its flags.
+    ///
+    /// Returns `None` if the TCP header is truncated.
+    fn classify(transport: &[u8]) -> Option<Self> {
+        let flags = *transport.get(13)?;
+
+        let segment = if flags & TCP_FLAG_RST != 0 {
+            Self::Rst
+        } else if flags & TCP_FLAG_SYN != 0 {
+            if flags & TCP_FLAG_ACK != 0 {
+                Self::SynAck
+            } else {
+                Self::Syn
+            }
+        } else if flags & TCP_FLAG_FIN != 0 {
+            Self::Fin
+        } else if flags & TCP_FLAG_ACK != 0 {
+            Self::Ack
+        } else {
+            Self::None
+        };
+        Some(segment)
+    }
+}
+
+impl TcpState {
+    /// Returns the initial state of a connection that starts with a segment.
+    ///
+    /// Like Linux, connections can be picked up in the middle, so both SYN segments and ACK
+    /// segments can start new connections.
+    fn start(segment: TcpSegment) -> Option<Self> {
+        match segment {
+            TcpSegment::Syn => Some(Self::SynSent),
+            TcpSegment::Ack => Some(Self::Established),
+            TcpSegment::SynAck | TcpSegment::Fin | TcpSegment::Rst | TcpSegment::None => None,
+        }
+    }
+
+    /// Returns the transition of the state after a segment is seen in a direction.
+    fn transit(self, segment: TcpSegment, is_reply: bool) -> TcpTransition {
+        use TcpState::*;
+        use TcpTransition::{Ignore, Invalid, Reopen, To};
+
+        match (segment, is_reply, self) {
+            (TcpSegment::Rst, _, _) => To(Close),
+            (TcpSegment::None, _, _) => Invalid,
+
+            (TcpSegment::Syn, false, SynSent) => To(SynSent),
+            (TcpSegment::Syn, false, TimeWait | Close) => Reopen,
+            (TcpSegment::Syn, false, _) => Ignore,
+            (TcpSegment::Syn, true, SynSent) => Ignore,
+            (TcpSegment::Syn, true, TimeWait) => Reopen,
+            (TcpSegment::Syn, true, _) => Invalid,
+
+            (TcpSegment::SynAck, false, SynRecv) => To(SynRecv),
+            (TcpSegment::SynAck, false, _) => Invalid,
+            (TcpSegment::SynAck, true, SynSent) => To(SynRecv),
+            (TcpSegment::SynAck, true, _) => Ignore,
+
+            (TcpSegment::Fin, _, SynSent) => Invalid,
+            (TcpSegment::Fin, _, SynRecv | Established) => To(FinWait),
+            (TcpSegment::Fin, _, FinWait | CloseWait | LastAck) => To(LastAck),
+            (TcpSegment::Fin, _, TimeWait) => To(TimeWait),
+            (TcpSegment::Fin, _, Close) => To(Close),
+
+            (TcpSegment::Ack, false, SynSent) => Invalid,
+            (TcpSegment::Ack, true, SynSent) => Ignore,
+            (TcpSegment::Ack, false, SynRecv) => To(Established),
+            (TcpSegment::Ack, true, SynRecv) => To(SynRecv),
+            (TcpSegment::Ack, _, Established) => To(Established),
+            (TcpSegment::Ack, _, FinWait | CloseWait) => To(CloseWait),
+            (TcpSegment::Ack, _, LastAck | TimeWait) => To(TimeWait),
+            (TcpSegment::Ack, _, Close) => To(Close),
+        }
+    }
+
+    /// Returns how long a connection in the state can stay idle.
+    fn timeout(self) -> Duration {
+        const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+        let secs = match self {
+            Self::SynSent => 120,
+            Self::SynRecv => 60,
+            Self::Established => 5 * SECS_PER_DAY,
+            Self::FinWait => 120,
+            Self::CloseWait => 60,
+            Self::LastAck => 30,
+            Self::TimeWait => 120,
+            Self::Close => 10,
+        };
+        Duration::from_secs(secs)
+    }
+}
+
+/// The tuple that identifies a connection in one direction.
+///
+/// For ICMP echo requests and replies, both ports are the identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Tuple {
+    pub(super) nfproto: u8,
+    pub(super) src_addr: [u8; 16],
+    pub(super) dst_addr: [u8; 16],
+    pub(super) l4proto: u8,
+    pub(super) src_port: u16,
+    pub(super) dst_port: u16,
+}
+
+impl Tuple {
+    /// Extracts the tuple of a packet.
+    ///
+    /// Returns `None` if the transport header is truncated or cannot be tracked.
+    fn from_packet(pkt: &PacketInfo) -> Option<Self> {
+        let (src, dst) = pkt.addrs();
+        let mut src_addr = [0; 16];
+        src_addr[..src.len()].copy_from_slice(src);
+        let mut dst_addr = [0; 16];
+        dst_addr[..dst.len()].copy_from_slice(dst);
+
+        let transport = pkt.transport()?;
+        let (src_port, dst_port) = match pkt.l4proto() {
+            IPPROTO_TCP | IPPROTO_UDP => {
+                let ports = transport.get(..4)?;
+                (
+                    u16::from_be_bytes([ports[0], ports[1]]),
+                    u16::from_be_bytes([ports[2], ports[3]]),
+                )
+            }
+            IPPROTO_ICMP | IPPROTO_ICMPV6 => {
+                if !matches!(
+                    classify_icmp(pkt.nfproto(), pkt.l4proto(), transport),
+                    Some(IcmpKind::Echo)
+                ) {
+                    return None;
+                }
+                let header = transport.get(..8)?;
+                // The requests and the replies share the same identifier, so using it as both
+                // ports makes the reversed tuples match.
+                let id = u16::from_be_bytes([header[4], header[5]]);
+                (id, id)
+            }
+            _ => (0, 0),
+        };
+
+        Some(Self {
+            nfproto: pkt.nfproto() as u8,
+            src_addr,
+            dst_addr,
+            l4proto: pkt.l4proto(),
+            src_port,
+            dst_port,
+        })
+    }
+
+    pub(super) fn reversed(&self) -> Self {
+        Self {
+            nfproto: self.nfproto,
+            src_addr: self.dst_addr,
+            dst_addr: self.src_addr,
+            l4proto: self.l4proto,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+}
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+enum IcmpKind<'a> {
+    /// An echo request or reply.
+    Echo,
+    /// An error message with the embedded packet, which is `None` if it is malformed.
+    Error(Option<PacketInfo<'a>>),
+    /// Other messages.
+    Other,
+}
+
+/// Classifies an ICMP (or ICMPv6) message.
+///
+/// Returns `None` if the packet is not an ICMP message.
+fn classify_icmp<'a>(nfproto: NfProto, l4proto: u8, transport: &'a [u8]) -> Option<IcmpKind<'a>> {
+    let is_v6 = match (nfproto, l4proto) {
+        (NfProto::Ipv4, IPPROTO_ICMP) => false,
+        (NfProto::Ipv6, IPPROTO_ICMPV6) => true,
+        _ => return None,
+    };
+    let Some(&icmp_type) = transport.first() else {
+        return Some(IcmpKind::Other);
+    };
+
+    let is_error = if is_v6 {
+        // Destination Unreachable, Packet Too Big, Time Exceeded, and Parameter Problem
+        matches!(icmp_type, 1..=4)
+    } else {
+        // Destination Unreachable, Source Quench, Redirect, Time Exceeded, and Parameter Problem
+        matches!(icmp_type, 3 | 4 | 5 | 11 | 12)
+    };
+    if is_error {
+        let inner = transport
+            .get(8..)
+            .and_then(PacketInfo::parse)
+            .filter(|inner| inner.nfproto() == nfproto);
+        return Some(IcmpKind::Error(inner));
+    }
+
+    let is_echo = if is_v6 {
+        matches!(icmp_type, ICMPV6_ECHO_REQUEST | ICMPV6_ECHO_REPLY)
+    } else {
+        matches!(icmp_type, ICMP_ECHO_REQUEST | ICMP_ECHO_REPLY)
+    };
+    if is_echo {
+        Some(IcmpKind::Echo)
+    } else {
+        Some(IcmpKind::Other)
+    }
+}
@@ -0,0 +1,463 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aster_bigtcp::iface::{FilterHook, RejectWith};
+
+use super::{
+    conntrack::CtState,
+    nat::{NatFlags, NatRange, NatType},
+    packet::PacketInfo,
+    ruleset::NfProto,
+};
+use crate::{net::iface::iter_all_ifaces, prelude::*};
+
+/// An expression in a rule.
+///
+/// Reference: <https://wiki.nftables.org/wiki-nftables/index.php/Understanding_nft_expressions>.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// Loads data from the packet.
+    Payload {
+        base: PayloadBase,
+        offset: u32,
+        len: u32,
+        dreg: Register,
+    },
+    /// Loads the metadata of the packet.
+    Meta { key: MetaKey, dreg: Register },
+    /// Loads the connection tracking information of the packet.
+    Ct { key: CtKey, dreg: Register },
+    /// Compares the data in a register.
+    ///
+    /// The rule stops matching if the comparison fails.
+    Cmp {
+        sreg: Register,
+        op: CmpOp,
+        data: Vec<u8>,
+    },
+    /// Computes `(sreg & mask) ^ xor` and stores the result in `dreg`.
+    Bitwise {
+        sreg: Register,
+        dreg: Register,
+        mask: Vec<u8>,
+        xor: Vec<u8>,
+    },
+    /// Stores data in a register, or issues a verdict.
+    Immediate(ImmediateData),
+    /// Counts the packets.
+    Counter(Arc<Counter>),
+    /// Rejects the packet.
+    Reject(RejectWith),
+    /// Translates the source or the destination of the connection.
+    ///
+    /// The addresses and the ports are loaded from the registers. The expression has no effect
+    /// on the packets in other families.
+    Nat {
+        type_: NatType,
+        family: NfProto,
+        addrs: Option<(Register, Register)>,
+        ports: Option<(Register, Register)>,
+        flags: NatFlags,
+    },
+    /// Translates the source of the connection to the address of the outgoing iface.
+    ///
+    /// The ports are loaded from the registers.
+    Masq {
+        ports: Option<(Register, Register)>,
+        flags: NatFlags,
+    },
+}
+
+/// The header from which [`Expr::Payload`] loads data.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum PayloadBase {
+    LinkLayer = 0,
+    Network = 1,
+    Transport = 2,
+}
+
+/// The metadata that [`Expr::Meta`] loads.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum MetaKey {
+    Len = 0,
+    Protocol = 1,
+    Iif = 4,
+    Oif = 5,
+    IifName = 6,
+    OifName = 7,
+    NfProto = 15,
+    L4Proto = 16,
+}
+
+impl MetaKey {
+    /// Returns the length of the loaded data.
+    pub fn data_len(self) -> usize {
+        match self {
+            Self::Len | Self::Iif | Self::Oif => size_of::<u32>(),
+            Self::Protocol => size_of::<u16>(),
+            Self::IifName | Self::OifName => IFNAMSIZ,
+            Self::NfProto | Self::L4Proto => size_of::<u8>(),
+        }
+    }
+
+    /// Loads the metadata into `buf`.
+    ///
+    /// Returns `None` if the metadata is unavailable.
+    fn load(self, cx: &EvalContext, buf: &mut [u8; IFNAMSIZ]) -> Option<()> {
+        match self {
+            Self::Len => buf[..4].copy_from_slice(&(cx.pkt.bytes().len() as u32).to_ne_bytes()),
+            Self::Protocol => {
+                let protocol = match cx.pkt.nfproto() {
+                    NfProto::Ipv6 => ETH_P_IPV6,
+                    _ => ETH_P_IP,
+                };
+                buf[..2].copy_from_slice(&protocol.to_be_bytes());
+            }
+            Self::Iif => buf[..4].copy_from_slice(&cx.iif()?.to_ne_bytes()),
+            Self::Oif => buf[..4].copy_from_slice(&cx.oif()?.to_ne_bytes()),
+            Self::IifName => *buf = iface_name(cx.iif()?)?,
+            Self::OifName => *buf = iface_name(cx.oif()?)?,
+            Self::NfProto => buf[0] = cx.pkt.nfproto() as u8,
+            Self::L4Proto => buf[0] = cx.pkt.l4proto(),
+        }
+        Some(())
+    }
+}
+
+const IFNAMSIZ: usize = 16;
+
+/// The connection tracking information that [`Expr::Ct`] loads.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum CtKey {
+    State = 0,
+}
+
+impl CtKey {
+    /// Returns the length of the loaded data.
+    pub fn data_len(self) -> usize {
+        match self {
+            Self::State => size_of::<u32>(),
+        }
+    }
+}
+
+/// The comparison operator of [`Expr::Cmp`].
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum CmpOp {
+    Eq = 0,
+    Neq = 1,
+    Lt = 2,
+    Lte = 3,
+    Gt = 4,
+    Gte = 5,
+}
+
+/// The data that [`Expr::Immediate`] stores.
+#[derive(Debug, Clone)]
+pub enum ImmediateData {
+    Value { dreg: Register, value: Vec<u8> },
+    Verdict(Verdict),
+}
+
+/// A verdict that is issued by a rule or a chain policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Drop,
+    /// Continues with the next rule.
+    Continue,
+    /// Stops evaluating the current rule.
+    Break,
+    /// Continues with the rules in another chain, and comes back after that chain returns.
+    Jump(String),
+    /// Continues with the rules in another chain without coming back.
+    Goto(String),
+    /// Returns from the current chain.
+    Return,
+}
+
+/// The counter of packets and bytes.
+#[derive(Debug, Default)]
+pub struct Counter {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Counter {
+    pub fn new(packets: u64, bytes: u64) -> Self {
+        Self {
+            packets: AtomicU64::new(packets),
+            bytes: AtomicU64::new(bytes),
+        }
+    }
+
+    /// Returns the number of packets and the number of bytes.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.packets.load(Ordering::Relaxed),
+            self.bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    fn add(&self, bytes: usize) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// The maximum length of the data that can be stored in the registers.
+const MAX_REG_DATA_LEN: usize = 64;
+
+/// A data register.
+///
+/// There are four 128-bit registers (1-4) and sixteen 32-bit registers (8-23). They overlap with
+/// each other, so the 128-bit register 1 is the same as the 32-bit registers 8-11.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    number: u32,
+    len: usize,
+}
+
+impl Register {
+    /// Creates a register that holds `len` bytes of data.
+    ///
+    /// The data can span consecutive registers.
+    pub fn new(number: u32, len: usize) -> Result<Self> {
+        let Some(offset) = Self::offset_of(number) else {
+            return_errno_with_message!(Errno::EINVAL, "the register is invalid");
+        };
+        if len == 0 || offset + len > MAX_REG_DATA_LEN {
+            return_errno_with_message!(Errno::ERANGE, "the data does not fit in the registers");
+        }
+
+        Ok(Self { number, len })
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    fn offset_of(number: u32) -> Option<usize> {
+        match number {
+            1..=4 => Some((number as usize - 1) * 16),
+            8..=23 => Some((number as usize - 8) * 4),
+            _ => None,
+        }
+    }
+
+    fn range(&self) -> core::ops::Range<usize> {
+        let offset = Self::offset_of(self.number).unwrap();
+        offset..offset + self.len
+    }
+}
+
+/// The context in which the expressions are evaluated.
+pub(super) struct EvalContext<'a> {
+    pub(super) pkt: &'a PacketInfo<'a>,
+    pub(super) hook: FilterHook,
+    pub(super) iface_index: u32,
+    pub(super) ct_state: CtState,
+}
+
+impl EvalContext<'_> {
+    fn iif(&self) -> Option<u32> {
+        match self.hook {
+            FilterHook::PreRouting | FilterHook::Input | FilterHook::Forward => {
+                Some(self.iface_index)
+            }
+            FilterHook::Output | FilterHook::PostRouting => None,
+        }
+    }
+
+    fn oif(&self) -> Option<u32> {
+        match self.hook {
+            FilterHook::Forward | FilterHook::Output | FilterHook::PostRouting => {
+                Some(self.iface_index)
+            }
+            FilterHook::PreRouting | FilterHook::Input => None,
+        }
+    }
+}
+
+/// The registers that are used to evaluate a rule.
+pub(super) struct Registers {
+    data: [u8; MAX_REG_DATA_LEN],
+}
+
+impl Registers {
+    pub(super) fn new() -> Self {
+        Self {
+            data: [0; MAX_REG_DATA_LEN],
+        }
+    }
+
+    fn load(&self, reg: Register) -> &[u8] {
+        &self.data[reg.range()]
+    }
+
+    /// Loads an address in network byte order.
+    fn load_addr(&self, reg: Register) -> [u8; 16] {
+        let mut addr = [0; 16];
+        addr[..reg.len].copy_from_slice(self.load(reg));
+        addr
+    }
+
+    /// Loads a port in network byte order.
+    fn load_port(&self, reg: Register) -> u16 {
+        let data = self.load(reg);
+        u16::from_be_bytes([data[0], data[1]])
+    }
+
+    /// Stores data in a register.
+    ///
+    /// Like Linux, the remaining bytes in the last 32-bit register are zeroed.
+    fn store(&mut self, reg: Register, data: &[u8]) {
+        let range = reg.range();
+        let padded_end = (range.start + data.len().next_multiple_of(4)).min(MAX_REG_DATA_LEN);
+        self.data[range.start..padded_end].fill(0);
+        self.data[range.start..range.start + data.len()].copy_from_slice(data);
+    }
+}
+
+/// The result of evaluating an expression.
+pub(super) enum Step<'a> {
+    /// Continues with the next expression.
+    Next,
+    /// Stops evaluating the current rule, since the rule does not match.
+    Break,
+    /// Stops evaluating the current rule with a verdict.
+    Verdict(&'a Verdict),
+    /// Rejects the packet.
+    Reject(RejectWith),
+    /// Accepts the packet and translates its connection.
+    Nat(NatRange),
+}
+
+impl Expr {
+    /// Returns the hooks at which the expression can be used if it is a NAT expression.
+    pub(super) fn nat_hooks(&self) -> Option<&'static [FilterHook]> {
+        match self {
+            Self::Nat { type_, .. } => Some(type_.hooks()),
+            Self::Masq { .. } => Some(&[FilterHook::PostRouting]),
+            _ => None,
+        }
+    }
+
+    pub(super) fn eval(&self, regs: &mut Registers, cx: &EvalContext) -> Step<'_> {
+        match self {
+            Self::Payload {
+                base,
+                offset,
+                len,
+                dreg,
+            } => {
+                let header_offset = match base {
+                    // The packets are filtered at the IP layer, so there are no link-layer
+                    // headers.
+                    PayloadBase::LinkLayer => return Step::Break,
+                    PayloadBase::Network => 0,
+                    PayloadBase::Transport => match cx.pkt.thoff() {
+                        Some(thoff) => thoff,
+                        None => return Step::Break,
+                    },
+                };
+                let start = header_offset + *offset as usize;
+                let Some(data) = cx.pkt.bytes().get(start..start + *len as usize) else {
+                    return Step::Break;
+                };
+                regs.store(*dreg, data);
+            }
+            Self::Meta { key, dreg } => {
+                let mut value = [0u8; IFNAMSIZ];
+                if key.load(cx, &mut value).is_none() {
+                    return Step::Break;
+                }
+                regs.store(*dreg, &value[..key.data_len()]);
+            }
+            Self::Ct { key, dreg } => match key {
+                CtKey::State => regs.store(*dreg, &cx.ct_state.bits().to_ne_bytes()),
+            },
+            Self::Cmp { sreg, op, data } => {
+                let ordering = regs.load(*sreg).cmp(data);
+                let is_matched = match op {
+                    CmpOp::Eq => ordering.is_eq(),
+                    CmpOp::Neq => ordering.is_ne(),
+                    CmpOp::Lt => ordering.is_lt(),
+                    CmpOp::Lte => ordering.is_le(),
+                    CmpOp::Gt => ordering.is_gt(),
+                    CmpOp::Gte => ordering.is_ge(),
+                };
+                if !is_matched {
+                    return Step::Break;
+                }
+            }
+            Self::Bitwise {
+                sreg,
+                dreg,
+                mask,
+                xor,
+            } => {
+                let mut result = [0u8; MAX_REG_DATA_LEN];
+                let result = &mut result[..mask.len()];
+                let src = regs.load(*sreg);
+                for (i, byte) in result.iter_mut().enumerate() {
+                    *byte = (src[i] & mask[i]) ^ xor[i];
+                }
+                regs.store(*dreg, result);
+            }
+            Self::Immediate(data) => match data {
+                ImmediateData::Value { dreg, value } => regs.store(*dreg, value),
+                ImmediateData::Verdict(verdict) => return Step::Verdict(verdict),
+            },
+            Self::Counter(counter) => counter.add(cx.pkt.bytes().len()),
+            Self::Reject(with) => return Step::Reject(*with),
+            Self::Nat {
+                family,
+                addrs,
+                ports,
+                flags,
+                ..
+            } => {
+                if !family.matches(cx.pkt.nfproto()) {
+                    return Step::Next;
+                }
+                return Step::Nat(NatRange {
+                    addrs: addrs.map(|(min, max)| (regs.load_addr(min), regs.load_addr(max))),
+                    ports: ports.map(|(min, max)| (regs.load_port(min), regs.load_port(max))),
+                    flags: *flags,
+                });
+            }
+            Self::Masq { ports, flags } => {
+                // TODO: Change the source addresses after packets can be forwarded. For now, the
+                // packets are always sent from the addresses of the outgoing ifaces.
+                return Step::Nat(NatRange {
+                    addrs: None,
+                    ports: ports.map(|(min, max)| (regs.load_port(min), regs.load_port(max))),
+                    flags: *flags,
+                });
+            }
+        }
+
+        Step::Next
+    }
+}
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+
+/// Returns the name of an iface, padded with zeros.
+fn iface_name(index: u32) -> Option<[u8; IFNAMSIZ]> {
+    let iface = iter_all_ifaces().find(|iface| iface.index() == index)?;
+
+    let mut name = [0; IFNAMSIZ];
+    let len = iface.name().len().min(IFNAMSIZ - 1);
+    name[..len].copy_from_slice(&iface.name().as_bytes()[..len]);
+    Some(name)
+}
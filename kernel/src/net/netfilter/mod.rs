@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A packet filter that is compatible with nftables.
+//!
+//! The network stack passes the IP packets to the filter at the netfilter-style hooks (see
+//! [`FilterHook`]). At each hook, the packets traverse the base chains attached to the hook in
+//! the order of their priorities. Each chain consists of rules, and each rule consists of
+//! expressions that load packet data into registers, compare the registers, and finally issue
+//! verdicts such as accepting, dropping, or rejecting the packets.
+//!
+//! Connections are tracked, so the rules can match the connection states. The connections can
+//! also be translated with NAT by the rules in NAT chains.
+//!
+//! The rules are configured from user space with `NETLINK_NETFILTER` sockets, which speak a
+//! subset of the nf_tables protocol. Changes are applied in transactions (i.e., batches), so the
+//! network stack always sees a consistent rule set.
+//!
+//! Reference: <https://wiki.nftables.org/wiki-nftables/index.php/Main_Page>.
+
+use aster_bigtcp::iface::{FilterHook, FilterVerdict, PacketFilter};
+
+pub use self::{
+    expr::{CmpOp, Counter, CtKey, Expr, ImmediateData, MetaKey, PayloadBase, Register, Verdict},
+    nat::{NatFlags, NatType},
+    ruleset::{
+        BaseChain, Chain, ChainType, NfProto, Rule, Ruleset, Table, Transaction, MAX_NAME_LEN,
+    },
+};
+
+mod conntrack;
+mod expr;
+mod nat;
+mod packet;
+mod ruleset;
+
+/// The packet filter that is invoked by the network stack.
+pub struct Netfilter;
+
+impl PacketFilter for Netfilter {
+    fn is_active() -> bool {
+        ruleset::is_active()
+    }
+
+    fn filter(iface_index: u32, hooks: &[FilterHook], packet: &mut [u8]) -> FilterVerdict {
+        ruleset::filter(iface_index, hooks, packet)
+    }
+}
+
+/// Returns the generation of the rule set, which increases each time the rule set is changed.
+pub fn generation() -> u32 {
+    ruleset::generation()
+}
+
+/// Calls `f` with the rule set that is currently in effect.
+pub fn with_ruleset<R>(f: impl FnOnce(&Ruleset) -> R) -> R {
+    ruleset::with_ruleset(f)
+}
@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Network address translation (NAT).
+//!
+//! The translation of a connection (i.e., its NAT binding) is set up by the `snat`, `dnat`, and
+//! `masquerade` expressions in the NAT chains when the first packet of the connection is
+//! filtered. The binding is recorded in the tuple of the connection in the reply direction (see
+//! [`super::conntrack`]), and the following packets in both directions are translated
+//! accordingly.
+//!
+//! Like Linux, the destinations are translated at the prerouting and output hooks, and the
+//! sources are translated at the input and postrouting hooks. Packets are never forwarded, so
+//! only the traffic that is sent or received locally can be translated.
+//!
+//! Reference: <https://wiki.nftables.org/wiki-nftables/index.php/Performing_Network_Address_Translation_(NAT)>.
+
+use aster_bigtcp::iface::FilterHook;
+
+use super::{
+    conntrack::{Tuple, IPPROTO_ICMP, IPPROTO_ICMPV6, IPPROTO_TCP, IPPROTO_UDP},
+    packet::PacketInfo,
+    ruleset::NfProto,
+};
+use crate::{prelude::*, util::random::getrandom};
+
+/// The type of [`super::Expr::Nat`].
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum NatType {
+    /// Translates the source.
+    Snat = 0,
+    /// Translates the destination.
+    Dnat = 1,
+}
+
+impl NatType {
+    /// Returns the hooks at which the translation can be set up.
+    pub(super) fn hooks(self) -> &'static [FilterHook] {
+        match self {
+            Self::Snat => &[FilterHook::Input, FilterHook::PostRouting],
+            Self::Dnat => &[FilterHook::PreRouting, FilterHook::Output],
+        }
+    }
+}
+
+bitflags! {
+    /// The flags of a NAT range.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_nat.h>.
+    pub struct NatFlags: u32 {
+        /// The addresses are specified.
+        const MAP_IPS            = 1 << 0;
+        /// The ports are specified.
+        const PROTO_SPECIFIED    = 1 << 1;
+        /// The ports are chosen randomly.
+        const PROTO_RANDOM       = 1 << 2;
+        /// The same address is chosen for the same source regardless of the destination.
+        const PERSISTENT         = 1 << 3;
+        /// The ports are chosen randomly.
+        const PROTO_RANDOM_FULLY = 1 << 4;
+    }
+}
+
+/// The range of the addresses and the ports that are chosen by NAT.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct NatRange {
+    /// The minimum and the maximum addresses in network byte order.
+    ///
+    /// This is `None` if the addresses are not changed.
+    pub(super) addrs: Option<([u8; 16], [u8; 16])>,
+    /// The minimum and the maximum ports.
+    ///
+    /// This is `None` if the ports are chosen automatically.
+    pub(super) ports: Option<(u16, u16)>,
+    pub(super) flags: NatFlags,
+}
+
+/// The part of a packet that is translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ManipType {
+    Src,
+    Dst,
+}
+
+impl ManipType {
+    /// Returns the part of the packets that is translated at a hook.
+    ///
+    /// Returns `None` if no packets are translated at the hook.
+    pub(super) fn of_hook(hook: FilterHook) -> Option<Self> {
+        match hook {
+            FilterHook::PreRouting | FilterHook::Output => Some(Self::Dst),
+            FilterHook::Input | FilterHook::PostRouting => Some(Self::Src),
+            FilterHook::Forward => None,
+        }
+    }
+
+    /// Returns the priority at which the packets are translated.
+    ///
+    /// Reference: `NF_IP_PRI_NAT_DST` and `NF_IP_PRI_NAT_SRC` in Linux.
+    pub(super) fn priority(self) -> i32 {
+        match self {
+            Self::Dst => -100,
+            Self::Src => 100,
+        }
+    }
+}
+
+/// The maximum number of ports that are tried to find a unique tuple.
+const MAX_PORT_ATTEMPTS: u32 = 128;
+
+/// Chooses the tuple of the packets in the original direction after being translated.
+///
+/// The source (or the destination) of `tuple` is changed according to `range`. If `range` is
+/// `None`, the source port is still changed if the tuple is used by another connection.
+///
+/// Returns `false` if no unused tuples can be found.
+pub(super) fn choose_tuple(
+    tuple: &mut Tuple,
+    manip: ManipType,
+    range: Option<&NatRange>,
+    is_used: impl Fn(&Tuple) -> bool,
+) -> bool {
+    if let Some(range) = range {
+        if let Some((min_addr, max_addr)) = range.addrs.as_ref() {
+            let addr = choose_addr(tuple, range.flags, min_addr, max_addr);
+            match manip {
+                ManipType::Src => tuple.src_addr = addr,
+                ManipType::Dst => tuple.dst_addr = addr,
+            }
+        }
+    }
+
+    let port = match (tuple.l4proto, manip) {
+        (IPPROTO_TCP | IPPROTO_UDP, ManipType::Src) => tuple.src_port,
+        (IPPROTO_TCP | IPPROTO_UDP, ManipType::Dst) => tuple.dst_port,
+        (IPPROTO_ICMP | IPPROTO_ICMPV6, _) => tuple.src_port,
+        // Other protocols have no ports, so clashes cannot be avoided.
+        (_, _) => return true,
+    };
+    let (min_port, max_port) = match (range.and_then(|range| range.ports), manip) {
+        (Some(ports), _) => ports,
+        // Like Linux, the destination ports are not changed unless they are specified.
+        (None, ManipType::Dst) => return true,
+        (None, ManipType::Src) => default_ports(tuple.l4proto, port),
+    };
+
+    let is_random = range.is_some_and(|range| {
+        range
+            .flags
+            .intersects(NatFlags::PROTO_RANDOM | NatFlags::PROTO_RANDOM_FULLY)
+    });
+    if !is_random && (min_port..=max_port).contains(&port) && !is_used(tuple) {
+        return true;
+    }
+
+    let num_ports = u32::from(max_port.saturating_sub(min_port)) + 1;
+    let mut offset = [0u8; 4];
+    getrandom(&mut offset);
+    let offset = u32::from_ne_bytes(offset);
+    for i in 0..num_ports.min(MAX_PORT_ATTEMPTS) {
+        let port = min_port + (offset.wrapping_add(i) % num_ports) as u16;
+        match tuple.l4proto {
+            // The identifier of ICMP echo messages is used as both ports.
+            IPPROTO_ICMP | IPPROTO_ICMPV6 => (tuple.src_port, tuple.dst_port) = (port, port),
+            _ if manip == ManipType::Src => tuple.src_port = port,
+            _ => tuple.dst_port = port,
+        }
+        if !is_used(tuple) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Returns the range of the source ports that are chosen if no ports are specified.
+///
+/// Like Linux, privileged ports are mapped to privileged ports, and the ports that are used by
+/// rsh and rlogin clients (i.e., 512-599) are avoided.
+fn default_ports(l4proto: u8, port: u16) -> (u16, u16) {
+    match (l4proto, port) {
+        (IPPROTO_ICMP | IPPROTO_ICMPV6, _) => (0, u16::MAX),
+        (_, 0..512) => (1, 511),
+        (_, 512..1024) => (600, 1023),
+        (_, 1024..) => (1024, u16::MAX),
+    }
+}
+
+/// Chooses an address in a range.
+///
+/// Like Linux, the address is chosen by hashing the addresses of the tuple, so that the
+/// connections between the same hosts are translated to the same address.
+fn choose_addr(tuple: &Tuple, flags: NatFlags, min: &[u8; 16], max: &[u8; 16]) -> [u8; 16] {
+    let len = addr_len(tuple.nfproto);
+    let to_u128 = |addr: &[u8]| {
+        let mut bytes = [0u8; 16];
+        bytes[16 - len..].copy_from_slice(&addr[..len]);
+        u128::from_be_bytes(bytes)
+    };
+
+    let (min_val, max_val) = (to_u128(min), to_u128(max));
+    if max_val <= min_val {
+        return *min;
+    }
+
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &tuple.src_addr[..len]);
+    if !flags.contains(NatFlags::PERSISTENT) {
+        hash = fnv1a(hash, &tuple.dst_addr[..len]);
+    }
+    let span = max_val - min_val;
+    let offset = match span.checked_add(1) {
+        Some(num_addrs) => u128::from(hash) % num_addrs,
+        None => u128::from(hash),
+    };
+
+    let mut addr = [0u8; 16];
+    addr[..len].copy_from_slice(&(min_val + offset).to_be_bytes()[16 - len..]);
+    addr
+}
+
+fn addr_len(nfproto: u8) -> usize {
+    if nfproto == NfProto::Ipv4 as u8 {
+        4
+    } else {
+        16
+    }
+}
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+fn fnv1a(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Rewrites the source (or the destination) of a packet to that of `target`.
+///
+/// The checksums are updated incrementally, so they stay invalid if they were invalid.
+pub(super) fn manip_packet(packet: &mut [u8], manip: ManipType, target: &Tuple) {
+    let Some(pkt) = PacketInfo::parse(packet) else {
+        return;
+    };
+    let nfproto = pkt.nfproto();
+    let l4proto = pkt.l4proto();
+    let thoff = pkt.thoff();
+
+    let (addr_offset, addr_len) = match (nfproto, manip) {
+        (NfProto::Ipv4, ManipType::Src) => (12, 4),
+        (NfProto::Ipv4, ManipType::Dst) => (16, 4),
+        (NfProto::Ipv6, ManipType::Src) => (8, 16),
+        (NfProto::Ipv6, ManipType::Dst) => (24, 16),
+        (NfProto::Inet, _) => unreachable!("a packet is either IPv4 or IPv6"),
+    };
+    let (new_addr, new_port) = match manip {
+        ManipType::Src => (&target.src_addr[..addr_len], target.src_port),
+        ManipType::Dst => (&target.dst_addr[..addr_len], target.dst_port),
+    };
+    let mut old_addr = [0u8; 16];
+    old_addr[..addr_len].copy_from_slice(&packet[addr_offset..addr_offset + addr_len]);
+    let old_addr = &old_addr[..addr_len];
+
+    if let Some(thoff) = thoff {
+        let transport = &mut packet[thoff..];
+        manip_transport(
+            transport,
+            nfproto,
+            l4proto,
+            manip,
+            (old_addr, new_addr),
+            new_port,
+        );
+    }
+
+    if old_addr != new_addr {
+        packet[addr_offset..addr_offset + addr_len].copy_from_slice(new_addr);
+        if nfproto == NfProto::Ipv4 {
+            update_checksum(
+                &mut packet[IPV4_CHECKSUM_OFFSET..IPV4_CHECKSUM_OFFSET + 2],
+                old_addr,
+                new_addr,
+            );
+        }
+    }
+}
+
+const IPV4_CHECKSUM_OFFSET: usize = 10;
+
+/// Rewrites the port (or the ICMP identifier) of a transport header and updates its checksum.
+fn manip_transport(
+    transport: &mut [u8],
+    nfproto: NfProto,
+    l4proto: u8,
+    manip: ManipType,
+    (old_addr, new_addr): (&[u8], &[u8]),
+    new_port: u16,
+) {
+    let port_offset = match manip {
+        ManipType::Src => 0,
+        ManipType::Dst => 2,
+    };
+    // The offsets of the checksum and the port, and whether the checksum covers the
+    // pseudo-header that contains the addresses.
+    let (checksum_offset, port_offset, has_pseudo_header) = match (nfproto, l4proto) {
+        (_, IPPROTO_TCP) => (16, port_offset, true),
+        (_, IPPROTO_UDP) => (6, port_offset, true),
+        // Only ICMP echo messages belong to connections, and they have identifiers.
+        (NfProto::Ipv4, IPPROTO_ICMP) => (2, 4, false),
+        (NfProto::Ipv6, IPPROTO_ICMPV6) => (2, 4, true),
+        (_, _) => return,
+    };
+    if transport.len() < checksum_offset.max(port_offset) + 2 {
+        return;
+    }
+
+    let old_port = [transport[port_offset], transport[port_offset + 1]];
+    let new_port = new_port.to_be_bytes();
+    transport[port_offset..port_offset + 2].copy_from_slice(&new_port);
+
+    let checksum = &mut transport[checksum_offset..checksum_offset + 2];
+    // A zero UDP checksum over IPv4 means that there is no checksum.
+    if l4proto == IPPROTO_UDP && nfproto == NfProto::Ipv4 && checksum == [0, 0] {
+        return;
+    }
+    if has_pseudo_header {
+        update_checksum(checksum, old_addr, new_addr);
+    }
+    update_checksum(checksum, &old_port, &new_port);
+    if l4proto == IPPROTO_UDP && checksum == [0, 0] {
+        checksum.copy_from_slice(&[0xff, 0xff]);
+    }
+}
+
+/// Updates a checksum after the covered data changes from `old` to `new`.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc1624>.
+fn update_checksum(checksum: &mut [u8], old: &[u8], new: &[u8]) {
+    let mut sum = u32::from(!u16::from_be_bytes([checksum[0], checksum[1]]));
+    for (old, new) in old.chunks_exact(2).zip(new.chunks_exact(2)) {
+        sum += u32::from(!u16::from_be_bytes([old[0], old[1]]));
+        sum += u32::from(u16::from_be_bytes([new[0], new[1]]));
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    checksum.copy_from_slice(&(!(sum as u16)).to_be_bytes());
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::ruleset::NfProto;
+
+/// An IP packet whose headers have been located.
+pub(super) struct PacketInfo<'a> {
+    bytes: &'a [u8],
+    nfproto: NfProto,
+    l4proto: u8,
+    /// The offset of the transport header.
+    ///
+    /// This is `None` if the packet is a non-first fragment, which carries no transport header.
+    thoff: Option<usize>,
+}
+
+const IPV4_HEADER_MIN_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+
+impl<'a> PacketInfo<'a> {
+    /// Parses the IP header at the beginning of `bytes`.
+    ///
+    /// Returns `None` if the IP header is malformed.
+    pub(super) fn parse(bytes: &'a [u8]) -> Option<Self> {
+        match bytes.first()? >> 4 {
+            4 => {
+                if bytes.len() < IPV4_HEADER_MIN_LEN {
+                    return None;
+                }
+                let header_len = ((bytes[0] & 0xf) as usize) * 4;
+                if header_len < IPV4_HEADER_MIN_LEN || header_len > bytes.len() {
+                    return None;
+                }
+                let frag_offset = u16::from_be_bytes([bytes[6], bytes[7]]) & 0x1fff;
+                Some(Self {
+                    bytes,
+                    nfproto: NfProto::Ipv4,
+                    l4proto: bytes[9],
+                    thoff: (frag_offset == 0).then_some(header_len),
+                })
+            }
+            6 => {
+                if bytes.len() < IPV6_HEADER_LEN {
+                    return None;
+                }
+                // TODO: Skip the extension headers. They are not supported by the network stack
+                // yet.
+                Some(Self {
+                    bytes,
+                    nfproto: NfProto::Ipv6,
+                    l4proto: bytes[6],
+                    thoff: Some(IPV6_HEADER_LEN),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub(super) fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub(super) fn nfproto(&self) -> NfProto {
+        self.nfproto
+    }
+
+    pub(super) fn l4proto(&self) -> u8 {
+        self.l4proto
+    }
+
+    /// Returns the source and destination addresses in network byte order.
+    pub(super) fn addrs(&self) -> (&'a [u8], &'a [u8]) {
+        match self.nfproto {
+            NfProto::Ipv4 => (&self.bytes[12..16], &self.bytes[16..20]),
+            NfProto::Ipv6 => (&self.bytes[8..24], &self.bytes[24..40]),
+            NfProto::Inet => unreachable!("a packet is either IPv4 or IPv6"),
+        }
+    }
+
+    /// Returns the transport header and payload.
+    pub(super) fn transport(&self) -> Option<&'a [u8]> {
+        self.thoff.map(|thoff| &self.bytes[thoff..])
+    }
+
+    /// Returns the offset of the transport header.
+    pub(super) fn thoff(&self) -> Option<usize> {
+        self.thoff
+    }
+}
@@ -0,0 +1,705 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use aster_bigtcp::iface::{FilterHook, FilterVerdict};
+use aster_softirq::BottomHalfDisabled;
+
+use super::{
+    conntrack::{self, CtInfo},
+    expr::{EvalContext, Expr, ImmediateData, Registers, Step, Verdict},
+    nat::{ManipType, NatRange},
+    packet::PacketInfo,
+};
+use crate::prelude::*;
+
+/// The maximum length of table names and chain names, including the terminating zero.
+pub const MAX_NAME_LEN: usize = 256;
+
+/// The maximum depth of nested jumps.
+const MAX_JUMP_DEPTH: usize = 16;
+
+/// The family of a table.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter.h>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum NfProto {
+    /// Both IPv4 and IPv6.
+    Inet = 1,
+    Ipv4 = 2,
+    Ipv6 = 10,
+}
+
+impl NfProto {
+    pub(super) fn matches(self, pkt_nfproto: NfProto) -> bool {
+        self == NfProto::Inet || self == pkt_nfproto
+    }
+}
+
+/// A set of tables.
+#[derive(Debug, Clone)]
+pub struct Ruleset {
+    tables: Vec<Table>,
+    next_table_handle: u64,
+    /// The base chains that are attached to the hooks, sorted by their priorities.
+    ///
+    /// Each element consists of the table index and the chain index.
+    hooked_chains: Vec<(usize, usize)>,
+}
+
+/// A table, which holds chains.
+#[derive(Debug, Clone)]
+pub struct Table {
+    family: NfProto,
+    name: String,
+    handle: u64,
+    is_dormant: bool,
+    chains: Vec<Chain>,
+    /// The next handle of the chains and rules in the table.
+    next_handle: u64,
+}
+
+/// A chain, which holds rules.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    name: String,
+    handle: u64,
+    base: Option<BaseChain>,
+    rules: Vec<Rule>,
+}
+
+/// The properties of a base chain, which is attached to a hook.
+///
+/// Other chains can only be reached by jumping from the rules in the base chains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseChain {
+    pub type_: ChainType,
+    pub hook: FilterHook,
+    pub priority: i32,
+    /// The verdict if no rules in the chain issue a verdict.
+    ///
+    /// This must be either [`Verdict::Accept`] or [`Verdict::Drop`].
+    pub policy: Verdict,
+}
+
+/// The type of a base chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainType {
+    /// The chain filters packets.
+    Filter,
+    /// The chain sets up NAT for new connections.
+    ///
+    /// Like Linux, only the first packet of each connection traverses the chain. The packets
+    /// are translated at fixed priorities (i.e., -100 for destination NAT and 100 for source NAT)
+    /// regardless of the priority of the chain.
+    Nat,
+}
+
+/// A rule, which consists of expressions.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    handle: u64,
+    exprs: Vec<Expr>,
+}
+
+impl Ruleset {
+    const fn new() -> Self {
+        Self {
+            tables: Vec::new(),
+            next_table_handle: 1,
+            hooked_chains: Vec::new(),
+        }
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn table(&self, family: NfProto, name: &str) -> Result<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.family == family && table.name == name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))
+    }
+
+    pub fn table_mut(&mut self, family: NfProto, name: &str) -> Result<&mut Table> {
+        self.tables
+            .iter_mut()
+            .find(|table| table.family == family && table.name == name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))
+    }
+
+    /// Adds a new table.
+    pub fn add_table(&mut self, family: NfProto, name: String, is_dormant: bool) -> Result<()> {
+        if self.table(family, &name).is_ok() {
+            return_errno_with_message!(Errno::EEXIST, "the table already exists");
+        }
+
+        let handle = self.next_table_handle;
+        self.next_table_handle += 1;
+        self.tables.push(Table {
+            family,
+            name,
+            handle,
+            is_dormant,
+            chains: Vec::new(),
+            next_handle: 1,
+        });
+        Ok(())
+    }
+
+    /// Removes a table with all its chains and rules.
+    pub fn remove_table(&mut self, family: NfProto, name: &str) -> Result<()> {
+        let index = self
+            .tables
+            .iter()
+            .position(|table| table.family == family && table.name == name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?;
+        self.tables.remove(index);
+        Ok(())
+    }
+
+    /// Removes the tables in a family, or all tables if `family` is `None`.
+    pub fn clear(&mut self, family: Option<NfProto>) {
+        self.tables
+            .retain(|table| family.is_some_and(|family| family != table.family));
+    }
+
+    /// Evaluates the base chains attached to a hook, and translates the packet if its
+    /// connection is translated by NAT.
+    fn eval_hook(
+        &self,
+        hook: FilterHook,
+        iface_index: u32,
+        packet: &mut [u8],
+        ct_info: &mut CtInfo,
+    ) -> FilterVerdict {
+        let mut nat_manip = ManipType::of_hook(hook);
+
+        for (table, chain) in self.hooked_chains(hook, ChainType::Filter) {
+            if let Some(manip) =
+                nat_manip.take_if(|manip| chain_priority(chain) >= manip.priority())
+            {
+                match self.eval_nat(manip, hook, iface_index, packet, ct_info) {
+                    FilterVerdict::Accept => (),
+                    verdict => return verdict,
+                }
+            }
+
+            // The packet is parsed again because NAT may have rewritten it. NAT does not change
+            // the layout of the headers, so the packet cannot become malformed.
+            let pkt = PacketInfo::parse(packet).unwrap();
+            if table.is_dormant || !table.family.matches(pkt.nfproto()) {
+                continue;
+            }
+
+            let cx = EvalContext {
+                pkt: &pkt,
+                hook,
+                iface_index,
+                ct_state: ct_info.state(),
+            };
+            // Accepted packets go on to the base chains with lower priorities, but dropped or
+            // rejected packets stop immediately.
+            match table.eval_chain(chain, &cx, &mut None) {
+                FilterVerdict::Accept => (),
+                verdict => return verdict,
+            }
+        }
+
+        match nat_manip {
+            Some(manip) => self.eval_nat(manip, hook, iface_index, packet, ct_info),
+            None => FilterVerdict::Accept,
+        }
+    }
+
+    /// Sets up the NAT binding of a new connection with the NAT chains attached to a hook, and
+    /// then translates the packet according to the binding.
+    fn eval_nat(
+        &self,
+        manip: ManipType,
+        hook: FilterHook,
+        iface_index: u32,
+        packet: &mut [u8],
+        ct_info: &mut CtInfo,
+    ) -> FilterVerdict {
+        if ct_info.needs_nat_binding(manip) {
+            let pkt = PacketInfo::parse(packet).unwrap();
+            let cx = EvalContext {
+                pkt: &pkt,
+                hook,
+                iface_index,
+                ct_state: ct_info.state(),
+            };
+
+            let mut nat_range = None;
+            for (table, chain) in self.hooked_chains(hook, ChainType::Nat) {
+                if table.is_dormant || !table.family.matches(pkt.nfproto()) {
+                    continue;
+                }
+                match table.eval_chain(chain, &cx, &mut nat_range) {
+                    FilterVerdict::Accept if nat_range.is_some() => break,
+                    FilterVerdict::Accept => (),
+                    verdict => return verdict,
+                }
+            }
+
+            if !ct_info.bind_nat(manip, nat_range.as_ref()) {
+                // Like Linux, the packet is dropped if its connection clashes with others.
+                return FilterVerdict::Drop;
+            }
+        }
+
+        ct_info.nat_packet(manip, packet);
+        FilterVerdict::Accept
+    }
+
+    /// Returns the base chains of a type that are attached to a hook.
+    fn hooked_chains(
+        &self,
+        hook: FilterHook,
+        type_: ChainType,
+    ) -> impl Iterator<Item = (&Table, &Chain)> {
+        self.hooked_chains
+            .iter()
+            .map(|&(table_index, chain_index)| {
+                let table = &self.tables[table_index];
+                (table, &table.chains[chain_index])
+            })
+            .filter(move |(_, chain)| {
+                let base = chain.base.as_ref().unwrap();
+                base.hook == hook && base.type_ == type_
+            })
+    }
+
+    /// Updates the base chains attached to the hooks after the tables are changed.
+    fn update_hooked_chains(&mut self) {
+        self.hooked_chains.clear();
+        for (table_index, table) in self.tables.iter().enumerate() {
+            for (chain_index, chain) in table.chains.iter().enumerate() {
+                if chain.base.is_some() {
+                    self.hooked_chains.push((table_index, chain_index));
+                }
+            }
+        }
+
+        let tables = &self.tables;
+        self.hooked_chains
+            .sort_by_key(|&(table_index, chain_index)| {
+                chain_priority(&tables[table_index].chains[chain_index])
+            });
+    }
+}
+
+fn chain_priority(chain: &Chain) -> i32 {
+    chain.base.as_ref().unwrap().priority
+}
+
+impl Table {
+    pub fn family(&self) -> NfProto {
+        self.family
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    pub fn is_dormant(&self) -> bool {
+        self.is_dormant
+    }
+
+    pub fn set_dormant(&mut self, is_dormant: bool) {
+        self.is_dormant = is_dormant;
+    }
+
+    pub fn chains(&self) -> &[Chain] {
+        &self.chains
+    }
+
+    pub fn chain(&self, name: &str) -> Result<&Chain> {
+        self.chains
+            .iter()
+            .find(|chain| chain.name == name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))
+    }
+
+    pub fn chain_mut(&mut self, name: &str) -> Result<&mut Chain> {
+        self.chains
+            .iter_mut()
+            .find(|chain| chain.name == name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))
+    }
+
+    /// Adds a new chain, which is a base chain if `base` is not `None`.
+    pub fn add_chain(&mut self, name: String, base: Option<BaseChain>) -> Result<()> {
+        if self.chain(&name).is_ok() {
+            return_errno_with_message!(Errno::EEXIST, "the chain already exists");
+        }
+        if base
+            .as_ref()
+            .is_some_and(|base| !matches!(base.policy, Verdict::Accept | Verdict::Drop))
+        {
+            return_errno_with_message!(Errno::EINVAL, "the chain policy is invalid");
+        }
+        if base
+            .as_ref()
+            .is_some_and(|base| base.type_ == ChainType::Nat && base.hook == FilterHook::Forward)
+        {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "NAT chains cannot be attached to the forward hook"
+            );
+        }
+
+        let handle = self.alloc_handle();
+        self.chains.push(Chain {
+            name,
+            handle,
+            base,
+            rules: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Removes an empty chain that is not referenced by other chains.
+    pub fn remove_chain(&mut self, name: &str) -> Result<()> {
+        let chain = self.chain(name)?;
+        if !chain.rules.is_empty() || self.num_references(name) != 0 {
+            return_errno_with_message!(Errno::EBUSY, "the chain is in use");
+        }
+
+        self.chains.retain(|chain| chain.name != name);
+        Ok(())
+    }
+
+    /// Removes all rules in all chains.
+    pub fn flush(&mut self) {
+        self.chains.iter_mut().for_each(Chain::flush);
+    }
+
+    /// Returns the number of the rules that jump to a chain.
+    pub fn num_references(&self, name: &str) -> usize {
+        self.chains
+            .iter()
+            .flat_map(|chain| chain.rules.iter())
+            .filter(|rule| rule.jump_target().is_some_and(|target| target == name))
+            .count()
+    }
+
+    /// Creates a rule that can be added to a chain.
+    ///
+    /// The jump targets in the expressions are checked, so the rule will not create loops. The
+    /// NAT expressions are also checked, since they can only be used in the NAT chains attached
+    /// to specific hooks.
+    pub fn new_rule(&mut self, chain: &str, exprs: Vec<Expr>) -> Result<Rule> {
+        let rule = Rule { handle: 0, exprs };
+
+        let base = self.chain(chain)?.base.as_ref();
+        for nat_hooks in rule.exprs.iter().filter_map(Expr::nat_hooks) {
+            if !base
+                .is_some_and(|base| base.type_ == ChainType::Nat && nat_hooks.contains(&base.hook))
+            {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "the NAT expression cannot be used in the chain"
+                );
+            }
+        }
+
+        if let Some(target) = rule.jump_target() {
+            let target_chain = self.chain(target)?;
+            if target_chain.base.is_some() {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "cannot jump to base chains");
+            }
+            if self.is_reachable(target, chain, 0) {
+                return_errno_with_message!(Errno::ELOOP, "the jump creates a loop");
+            }
+        }
+
+        Ok(Rule {
+            handle: self.alloc_handle(),
+            ..rule
+        })
+    }
+
+    /// Returns whether the chain `to` can be reached from the chain `from` by jumps.
+    fn is_reachable(&self, from: &str, to: &str, depth: usize) -> bool {
+        if from == to {
+            return true;
+        }
+        if depth >= MAX_JUMP_DEPTH {
+            // Like Linux, overly nested jumps are treated as loops.
+            return true;
+        }
+
+        let Ok(chain) = self.chain(from) else {
+            return false;
+        };
+        chain
+            .rules
+            .iter()
+            .filter_map(Rule::jump_target)
+            .any(|target| self.is_reachable(target, to, depth + 1))
+    }
+
+    fn alloc_handle(&mut self) -> u64 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        handle
+    }
+
+    /// Evaluates a base chain and the chains that it jumps to.
+    ///
+    /// If a NAT expression is evaluated, the packet is accepted and `nat_range` is set to the
+    /// range of the translation.
+    fn eval_chain(
+        &self,
+        base: &Chain,
+        cx: &EvalContext,
+        nat_range: &mut Option<NatRange>,
+    ) -> FilterVerdict {
+        let mut regs = Registers::new();
+        let mut stack = Vec::new();
+        let mut chain = base;
+        let mut next_rule = 0;
+
+        loop {
+            let mut verdict = None;
+            while let Some(rule) = chain.rules.get(next_rule) {
+                next_rule += 1;
+                match rule.eval(&mut regs, cx) {
+                    Step::Next
+                    | Step::Break
+                    | Step::Verdict(Verdict::Continue | Verdict::Break) => {}
+                    Step::Verdict(rule_verdict) => {
+                        verdict = Some(rule_verdict);
+                        break;
+                    }
+                    Step::Reject(with) => return FilterVerdict::Reject(with),
+                    Step::Nat(range) => {
+                        *nat_range = Some(range);
+                        return FilterVerdict::Accept;
+                    }
+                }
+            }
+
+            let (target, is_jump) = match verdict {
+                Some(Verdict::Accept) => return FilterVerdict::Accept,
+                Some(Verdict::Drop) => return FilterVerdict::Drop,
+                Some(Verdict::Jump(target)) => (target, true),
+                Some(Verdict::Goto(target)) => (target, false),
+                Some(Verdict::Return) | None => {
+                    if let Some((prev_chain, prev_next_rule)) = stack.pop() {
+                        chain = prev_chain;
+                        next_rule = prev_next_rule;
+                        continue;
+                    }
+                    return match base.base.as_ref().unwrap().policy {
+                        Verdict::Drop => FilterVerdict::Drop,
+                        _ => FilterVerdict::Accept,
+                    };
+                }
+                Some(Verdict::Continue | Verdict::Break) => unreachable!(),
+            };
+
+            // The jump targets are checked when rules are added, and chains cannot be removed
+            // while they are referenced, so the chain must exist.
+            let Ok(target_chain) = self.chain(target) else {
+                return FilterVerdict::Drop;
+            };
+            if is_jump {
+                if stack.len() >= MAX_JUMP_DEPTH {
+                    return FilterVerdict::Drop;
+                }
+                stack.push((chain, next_rule));
+            }
+            chain = target_chain;
+            next_rule = 0;
+        }
+    }
+}
+
+impl Chain {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    pub fn base(&self) -> Option<&BaseChain> {
+        self.base.as_ref()
+    }
+
+    /// Sets the policy of a base chain.
+    pub fn set_policy(&mut self, policy: Verdict) -> Result<()> {
+        let Some(base) = self.base.as_mut() else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the chain is not a base chain");
+        };
+        if !matches!(policy, Verdict::Accept | Verdict::Drop) {
+            return_errno_with_message!(Errno::EINVAL, "the chain policy is invalid");
+        }
+
+        base.policy = policy;
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Returns the index of a rule.
+    pub fn rule_index(&self, handle: u64) -> Result<usize> {
+        self.rules
+            .iter()
+            .position(|rule| rule.handle == handle)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the rule does not exist"))
+    }
+
+    /// Inserts a rule at an index.
+    pub fn insert_rule(&mut self, index: usize, rule: Rule) {
+        self.rules.insert(index, rule);
+    }
+
+    /// Replaces the rule at an index.
+    pub fn replace_rule(&mut self, index: usize, rule: Rule) {
+        self.rules[index] = rule;
+    }
+
+    /// Removes the rule at an index.
+    pub fn remove_rule(&mut self, index: usize) {
+        self.rules.remove(index);
+    }
+
+    /// Removes all rules.
+    pub fn flush(&mut self) {
+        self.rules.clear();
+    }
+}
+
+impl Rule {
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    pub fn exprs(&self) -> &[Expr] {
+        &self.exprs
+    }
+
+    /// Returns the chain that the rule jumps to or goes to.
+    fn jump_target(&self) -> Option<&str> {
+        self.exprs.iter().find_map(|expr| match expr {
+            Expr::Immediate(ImmediateData::Verdict(
+                Verdict::Jump(target) | Verdict::Goto(target),
+            )) => Some(target.as_str()),
+            _ => None,
+        })
+    }
+
+    fn eval(&self, regs: &mut Registers, cx: &EvalContext) -> Step<'_> {
+        for expr in self.exprs.iter() {
+            match expr.eval(regs, cx) {
+                Step::Next => (),
+                step => return step,
+            }
+        }
+
+        Step::Next
+    }
+}
+
+/// A transaction that changes the rule set.
+///
+/// The changes take effect only after the transaction is committed. Only one transaction can be
+/// in progress at any time.
+pub struct Transaction {
+    ruleset: Ruleset,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Transaction {
+    /// Begins a transaction, waiting for the transaction in progress to finish.
+    pub fn begin() -> Self {
+        let guard = TRANSACTION_LOCK.lock();
+        let ruleset = RULESET.read().clone();
+
+        Self {
+            ruleset,
+            _guard: guard,
+        }
+    }
+
+    /// Commits the changes.
+    pub fn commit(mut self) {
+        self.ruleset.update_hooked_chains();
+        IS_ACTIVE.store(!self.ruleset.hooked_chains.is_empty(), Ordering::Relaxed);
+
+        let old_ruleset = core::mem::replace(&mut *RULESET.write(), self.ruleset);
+        // Drop the old rule set after the lock is released.
+        drop(old_ruleset);
+
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Deref for Transaction {
+    type Target = Ruleset;
+
+    fn deref(&self) -> &Self::Target {
+        &self.ruleset
+    }
+}
+
+impl DerefMut for Transaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.ruleset
+    }
+}
+
+static RULESET: RwLock<Ruleset, BottomHalfDisabled> = RwLock::new(Ruleset::new());
+static TRANSACTION_LOCK: Mutex<()> = Mutex::new(());
+static IS_ACTIVE: AtomicBool = AtomicBool::new(false);
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+pub(super) fn is_active() -> bool {
+    IS_ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(super) fn generation() -> u32 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+pub(super) fn with_ruleset<R>(f: impl FnOnce(&Ruleset) -> R) -> R {
+    f(&RULESET.read())
+}
+
+pub(super) fn filter(iface_index: u32, hooks: &[FilterHook], packet: &mut [u8]) -> FilterVerdict {
+    let Some(pkt) = PacketInfo::parse(packet) else {
+        // Malformed packets will be discarded by the network stack.
+        return FilterVerdict::Accept;
+    };
+    let mut ct_info = conntrack::track(&pkt);
+
+    let ruleset = RULESET.read();
+    for &hook in hooks {
+        match ruleset.eval_hook(hook, iface_index, packet, &mut ct_info) {
+            FilterVerdict::Accept => (),
+            verdict => return verdict,
+        }
+    }
+    drop(ruleset);
+
+    if !conntrack::confirm(ct_info) {
+        return FilterVerdict::Drop;
+    }
+    FilterVerdict::Accept
+}
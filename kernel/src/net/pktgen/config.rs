@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The parameters of pktgen devices.
+
+use core::{net::Ipv4Addr, time::Duration};
+
+use super::packet::{MAX_PKT_SIZE, MIN_PKT_SIZE};
+use crate::prelude::*;
+
+/// The parameters of a pktgen device.
+#[derive(Debug, Clone)]
+pub struct PktgenConfig {
+    /// The number of the packets to send, where zero means sending until stopped.
+    pub count: u64,
+    /// The size of the packets in bytes, excluding the frame check sequence.
+    pub pkt_size: usize,
+    /// The number of the packets queued before the device is notified.
+    pub burst: u32,
+    /// The interval between two packets.
+    pub delay: Duration,
+    /// The source MAC address, where `None` means the address of the device.
+    pub src_mac: Option<[u8; 6]>,
+    pub dst_mac: [u8; 6],
+    pub src_ip: Ipv4Addr,
+    pub dst_ip: Ipv4Addr,
+    pub udp_src: u16,
+    pub udp_dst: u16,
+}
+
+impl Default for PktgenConfig {
+    fn default() -> Self {
+        // The defaults are the same as Linux.
+        Self {
+            count: 1000,
+            pkt_size: 60,
+            burst: 1,
+            delay: Duration::ZERO,
+            src_mac: None,
+            dst_mac: [0; 6],
+            src_ip: Ipv4Addr::UNSPECIFIED,
+            dst_ip: Ipv4Addr::UNSPECIFIED,
+            udp_src: 9,
+            udp_dst: 9,
+        }
+    }
+}
+
+impl PktgenConfig {
+    /// Applies a command written to the device file, e.g., `count 1000`.
+    ///
+    /// Returns the result message, which is shown when the device file is read.
+    pub fn apply_command(&mut self, command: &str) -> Result<String> {
+        let mut args = command.split_whitespace();
+        let Some(name) = args.next() else {
+            return_errno_with_message!(Errno::EINVAL, "the command is empty");
+        };
+        let value = args.next().unwrap_or("");
+
+        match name {
+            "count" => self.count = parse_num(value)?,
+            "pkt_size" | "min_pkt_size" | "max_pkt_size" => {
+                let pkt_size = parse_num(value)? as usize;
+                if !(MIN_PKT_SIZE..=MAX_PKT_SIZE).contains(&pkt_size) {
+                    return_errno_with_message!(Errno::EINVAL, "the packet size is out of range");
+                }
+                self.pkt_size = pkt_size;
+            }
+            "burst" => {
+                let burst = parse_num(value)?;
+                if burst == 0 || burst > u32::MAX as u64 {
+                    return_errno_with_message!(Errno::EINVAL, "the burst is out of range");
+                }
+                self.burst = burst as u32;
+            }
+            "delay" => self.delay = Duration::from_nanos(parse_num(value)?),
+            "rate" => {
+                // The rate is in Mb/s, where the unit suffix (e.g., `300M`) is optional.
+                let rate = parse_num(value.trim_end_matches('M'))?;
+                if rate == 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the rate is zero");
+                }
+                let bits = self.pkt_size as u64 * 8;
+                self.delay = Duration::from_nanos(bits * 1000 / rate);
+            }
+            "ratep" => {
+                let pps = parse_num(value)?;
+                if pps == 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the rate is zero");
+                }
+                self.delay = Duration::from_nanos(1_000_000_000 / pps);
+            }
+            "src_mac" => self.src_mac = Some(parse_mac(value)?),
+            "dst_mac" => self.dst_mac = parse_mac(value)?,
+            "src_min" | "src_max" => self.src_ip = parse_ipv4(value)?,
+            "dst" | "dst_min" | "dst_max" => self.dst_ip = parse_ipv4(value)?,
+            "udp_src_min" | "udp_src_max" => self.udp_src = parse_port(value)?,
+            "udp_dst_min" | "udp_dst_max" => self.udp_dst = parse_port(value)?,
+            // The packets are always copied by the drivers, so cloning has no effect.
+            "clone_skb" => {
+                parse_num(value)?;
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the parameter does not exist"),
+        }
+
+        Ok(format!("OK: {}={}", name, value))
+    }
+}
+
+impl core::fmt::Display for PktgenConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let src_mac = self
+            .src_mac
+            .map_or_else(|| "device".to_string(), format_mac);
+        writeln!(
+            f,
+            "     count: {}  pkt_size: {}  burst: {}  delay: {}",
+            self.count,
+            self.pkt_size,
+            self.burst,
+            self.delay.as_nanos()
+        )?;
+        writeln!(f, "     dst: {}  src: {}", self.dst_ip, self.src_ip)?;
+        writeln!(
+            f,
+            "     src_mac: {}  dst_mac: {}",
+            src_mac,
+            format_mac(self.dst_mac)
+        )?;
+        writeln!(
+            f,
+            "     udp_src: {}  udp_dst: {}",
+            self.udp_src, self.udp_dst
+        )
+    }
+}
+
+fn parse_num(value: &str) -> Result<u64> {
+    value
+        .parse()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not a number"))
+}
+
+fn parse_port(value: &str) -> Result<u16> {
+    value
+        .parse()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not a port"))
+}
+
+fn parse_ipv4(value: &str) -> Result<Ipv4Addr> {
+    value
+        .parse()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not an IPv4 address"))
+}
+
+fn parse_mac(value: &str) -> Result<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut bytes = value.split(':');
+    for byte in mac.iter_mut() {
+        *byte = bytes
+            .next()
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the value is not a MAC address"))?;
+    }
+    if bytes.next().is_some() {
+        return_errno_with_message!(Errno::EINVAL, "the value is not a MAC address");
+    }
+    Ok(mac)
+}
+
+fn format_mac(mac: [u8; 6]) -> String {
+    format!(
+        "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    )
+}
@@ -0,0 +1,398 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A packet generator that sends UDP packets directly from the kernel.
+//!
+//! The packets bypass the socket layer and the network stack, so pktgen can measure the line rate
+//! of the network devices and their drivers. It is controlled by writing commands to the files in
+//! `/proc/net/pktgen`, whose interface is compatible with Linux:
+//!
+//! ```text
+//! echo "add_device eth0" > /proc/net/pktgen/kpktgend_0
+//! echo "count 100000" > /proc/net/pktgen/eth0
+//! echo "dst 10.0.2.2" > /proc/net/pktgen/eth0
+//! echo "start" > /proc/net/pktgen/pgctrl
+//! cat /proc/net/pktgen/eth0
+//! ```
+//!
+//! Reference: <https://docs.kernel.org/networking/pktgen.html>.
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_network::{AnyNetworkDevice, VirtioNetError};
+use aster_softirq::BottomHalfDisabled;
+use aster_time::read_monotonic_time;
+use ostd::sync::WaitQueue;
+
+pub use self::config::PktgenConfig;
+use self::packet::{build_packet, stamp_packet};
+use crate::{
+    net::iface::virtio_iface,
+    prelude::*,
+    sched::{Nice, SchedPolicy},
+    thread::{kernel_thread::ThreadOptions, Thread},
+};
+
+mod config;
+mod packet;
+
+/// The devices that are added to pktgen.
+static DEVICES: Mutex<Vec<Arc<PktgenDevice>>> = Mutex::new(Vec::new());
+
+/// The wait queue that is woken up when a device stops sending.
+static DONE_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+/// If the time until the next packet is shorter than this, pktgen spins instead of sleeping.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(100);
+
+/// Adds a network device to pktgen.
+pub fn add_device(name: &str) -> Result<()> {
+    let mut devices = DEVICES.lock();
+    if devices.iter().any(|device| device.is_running()) {
+        return_errno_with_message!(Errno::EBUSY, "pktgen is running");
+    }
+    if devices.iter().any(|device| device.name == name) {
+        return_errno_with_message!(Errno::EEXIST, "the device is already added");
+    }
+
+    let device = PktgenDevice::new(name)?;
+    devices.push(device);
+    Ok(())
+}
+
+/// Removes all the devices from pktgen.
+pub fn remove_all_devices() -> Result<()> {
+    let mut devices = DEVICES.lock();
+    if devices.iter().any(|device| device.is_running()) {
+        return_errno_with_message!(Errno::EBUSY, "pktgen is running");
+    }
+    devices.clear();
+    Ok(())
+}
+
+/// Returns all the devices added to pktgen.
+pub fn devices() -> Vec<Arc<PktgenDevice>> {
+    DEVICES.lock().clone()
+}
+
+/// Returns the device with the name.
+pub fn get_device(name: &str) -> Option<Arc<PktgenDevice>> {
+    DEVICES
+        .lock()
+        .iter()
+        .find(|device| device.name == name)
+        .cloned()
+}
+
+/// Starts sending on all the devices and waits until they stop.
+///
+/// If the waiting is interrupted by a signal, all the devices are stopped, which is the same as
+/// Linux. So a running test can be stopped by pressing `Ctrl+C`.
+pub fn start() -> Result<()> {
+    let devices = devices();
+    if devices.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "no device is added");
+    }
+    if devices.iter().any(|device| device.is_running()) {
+        return_errno_with_message!(Errno::EBUSY, "pktgen is running");
+    }
+
+    for device in devices.iter() {
+        device.start();
+    }
+
+    let res = DONE_WAIT_QUEUE.pause_until(|| {
+        devices
+            .iter()
+            .all(|device| !device.is_running())
+            .then_some(())
+    });
+    if res.is_err() {
+        stop();
+        DONE_WAIT_QUEUE.wait_until(|| {
+            devices
+                .iter()
+                .all(|device| !device.is_running())
+                .then_some(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Stops sending on all the devices.
+pub fn stop() {
+    for device in DEVICES.lock().iter() {
+        device.stop();
+    }
+}
+
+/// Stops sending on all the devices and removes them.
+pub fn reset() {
+    let mut devices = DEVICES.lock();
+    for device in devices.iter() {
+        device.stop();
+    }
+    devices.clear();
+}
+
+/// A network device that is added to pktgen.
+pub struct PktgenDevice {
+    name: String,
+    device: Arc<SpinLock<dyn AnyNetworkDevice, BottomHalfDisabled>>,
+    mac_addr: [u8; 6],
+    config: Mutex<PktgenConfig>,
+    stats: Mutex<PktgenStats>,
+    /// The result of the last command, which is reported in the device file.
+    result: Mutex<String>,
+    is_running: AtomicBool,
+    should_stop: AtomicBool,
+    /// The wait queue for pacing the packets, which is woken up when the device is stopped.
+    wait_queue: WaitQueue,
+}
+
+#[derive(Debug, Default, Clone)]
+struct PktgenStats {
+    sofar: u64,
+    errors: u64,
+    started_at: Duration,
+    stopped_at: Duration,
+    /// The time spent on waiting for the next packet or the transmit queue.
+    idle: Duration,
+    min_latency: Option<Duration>,
+    max_latency: Duration,
+    total_latency: Duration,
+}
+
+impl PktgenDevice {
+    fn new(name: &str) -> Result<Arc<Self>> {
+        // The interface name is more familiar to users than the device name.
+        let device_name = if name == "eth0" && virtio_iface().is_some() {
+            aster_virtio::device::network::DEVICE_NAME
+        } else {
+            name
+        };
+        let Some(device) = aster_network::get_device(device_name) else {
+            return_errno_with_message!(Errno::ENODEV, "the network device does not exist");
+        };
+        let mac_addr = device.lock().mac_addr().0;
+
+        // Send the packets to the gateway by default, just like the network stack does.
+        let mut config = PktgenConfig::default();
+        if name == "eth0" {
+            if let Some(ipv4_addr) = virtio_iface().and_then(|iface| iface.ipv4_addr()) {
+                config.src_ip = ipv4_addr;
+            }
+        }
+
+        Ok(Arc::new(Self {
+            name: name.to_string(),
+            device,
+            mac_addr,
+            config: Mutex::new(config),
+            stats: Mutex::new(PktgenStats::default()),
+            result: Mutex::new(String::new()),
+            is_running: AtomicBool::new(false),
+            should_stop: AtomicBool::new(false),
+            wait_queue: WaitQueue::new(),
+        }))
+    }
+
+    /// Returns the name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the device is sending.
+    pub fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::Acquire)
+    }
+
+    /// Applies a command that changes the configuration.
+    pub fn apply_command(&self, command: &str) -> Result<()> {
+        if self.is_running() {
+            return_errno_with_message!(Errno::EBUSY, "the device is sending");
+        }
+
+        let res = self.config.lock().apply_command(command);
+        *self.result.lock() = match &res {
+            Ok(message) => message.clone(),
+            Err(err) => format!("ERROR: {:?}", err),
+        };
+        res.map(|_| ())
+    }
+
+    fn start(self: &Arc<Self>) {
+        self.should_stop.store(false, Ordering::Relaxed);
+        self.is_running.store(true, Ordering::Release);
+        *self.stats.lock() = PktgenStats::default();
+
+        let device = self.clone();
+        ThreadOptions::new(move || {
+            device.run();
+            device.is_running.store(false, Ordering::Release);
+            DONE_WAIT_QUEUE.wake_all();
+        })
+        .sched_policy(SchedPolicy::Fair(Nice::MIN))
+        .spawn();
+    }
+
+    fn stop(&self) {
+        self.should_stop.store(true, Ordering::Relaxed);
+        self.wait_queue.wake_all();
+    }
+
+    fn run(&self) {
+        let config = self.config.lock().clone();
+        let mut packet = build_packet(&config, self.mac_addr);
+
+        let started_at = read_monotonic_time();
+        self.stats.lock().started_at = started_at;
+        let mut next_tx_at = started_at;
+
+        while !self.should_stop.load(Ordering::Relaxed) {
+            let sofar = self.stats.lock().sofar;
+            if config.count != 0 && sofar >= config.count {
+                break;
+            }
+
+            if !config.delay.is_zero() {
+                self.wait_until(next_tx_at);
+                next_tx_at += config.delay;
+            }
+
+            let sent_at = read_monotonic_time();
+            stamp_packet(&mut packet, sofar as u32, sent_at);
+            let is_sent = self.send(&packet);
+            let latency = read_monotonic_time() - sent_at;
+
+            let mut stats = self.stats.lock();
+            stats.sofar += 1;
+            if is_sent {
+                stats.min_latency = Some(stats.min_latency.map_or(latency, |min| min.min(latency)));
+                stats.max_latency = stats.max_latency.max(latency);
+                stats.total_latency += latency;
+            } else {
+                stats.errors += 1;
+            }
+            let is_burst_end = stats.sofar % config.burst as u64 == 0;
+            drop(stats);
+
+            if is_burst_end {
+                self.device.lock().notify_poll_end();
+            }
+        }
+
+        self.device.lock().notify_poll_end();
+        self.stats.lock().stopped_at = read_monotonic_time();
+    }
+
+    /// Waits until the time to send the next packet.
+    fn wait_until(&self, time: Duration) {
+        let now = read_monotonic_time();
+        if now >= time {
+            return;
+        }
+
+        let remaining = time - now;
+        if remaining > SPIN_THRESHOLD {
+            let _ = self.wait_queue.wait_until_or_timeout(
+                || self.should_stop.load(Ordering::Relaxed).then_some(()),
+                &(remaining - SPIN_THRESHOLD),
+            );
+        }
+        while read_monotonic_time() < time && !self.should_stop.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+
+        self.stats.lock().idle += read_monotonic_time() - now;
+    }
+
+    /// Sends a packet, retrying until the transmit queue has room.
+    ///
+    /// Returns whether the packet is sent.
+    fn send(&self, packet: &[u8]) -> bool {
+        loop {
+            let mut device = self.device.lock();
+            match device.send(packet) {
+                Ok(()) => return true,
+                Err(VirtioNetError::Busy) => (),
+                Err(_) => return false,
+            }
+
+            device.free_processed_tx_buffers();
+            if device.can_send() {
+                continue;
+            }
+            // Kick the device so that the queued packets are sent and the buffers are freed.
+            device.notify_poll_end();
+            drop(device);
+
+            if self.should_stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            let idle_since = read_monotonic_time();
+            Thread::yield_now();
+            self.stats.lock().idle += read_monotonic_time() - idle_since;
+        }
+    }
+
+    /// Returns the report of the device, whose format is the same as Linux.
+    pub fn report(&self) -> String {
+        let config = self.config.lock().clone();
+        let stats = self.stats.lock().clone();
+
+        let mut report = format!("Params:\n{}", config);
+
+        let now = if self.is_running() {
+            read_monotonic_time()
+        } else {
+            stats.stopped_at
+        };
+        report.push_str(&format!(
+            "Current:\n     pkts-sofar: {}  errors: {}\n     started: {}us  stopped: {}us idle: {}us\n",
+            stats.sofar,
+            stats.errors,
+            stats.started_at.as_micros(),
+            now.as_micros(),
+            stats.idle.as_micros()
+        ));
+
+        let result = self.result.lock().clone();
+        if self.is_running() || stats.sofar == 0 {
+            report.push_str(&format!("Result: {}\n", result));
+            return report;
+        }
+
+        let elapsed = stats.stopped_at.saturating_sub(stats.started_at);
+        let elapsed_us = elapsed.as_micros().max(1) as u64;
+        let sent = stats.sofar - stats.errors;
+        let pps = sent * 1_000_000 / elapsed_us;
+        let bps = pps * config.pkt_size as u64 * 8;
+        report.push_str(&format!(
+            "Result: OK: {}(c{}+d{}) usec, {} ({}byte,0frags)\n  {}pps {}Mb/sec ({}bps) errors: {}\n",
+            elapsed_us,
+            elapsed.saturating_sub(stats.idle).as_micros(),
+            stats.idle.as_micros(),
+            stats.sofar,
+            config.pkt_size,
+            pps,
+            bps / 1_000_000,
+            bps,
+            stats.errors
+        ));
+
+        if sent > 0 {
+            report.push_str(&format!(
+                "  latency: min {}ns  avg {}ns  max {}ns\n",
+                stats.min_latency.unwrap_or_default().as_nanos(),
+                stats.total_latency.as_nanos() / sent as u128,
+                stats.max_latency.as_nanos()
+            ));
+        }
+
+        report
+    }
+}
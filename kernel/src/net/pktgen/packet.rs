@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The UDP packets sent by pktgen.
+//!
+//! The layout is the same as Linux, so the packets can be recognized by the tools that parse the
+//! pktgen header:
+//!
+//! ```text
+//! | Ethernet (14) | IPv4 (20) | UDP (8) | magic | seq_num | tv_sec | tv_usec | padding |
+//! ```
+
+use core::time::Duration;
+
+use super::config::PktgenConfig;
+use crate::prelude::*;
+
+const ETH_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const PKTGEN_HEADER_LEN: usize = 16;
+
+const PKTGEN_HEADER_OFFSET: usize = ETH_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN;
+
+/// The minimum size of a packet, which holds all the headers.
+pub(super) const MIN_PKT_SIZE: usize = PKTGEN_HEADER_OFFSET + PKTGEN_HEADER_LEN;
+/// The maximum size of a packet, which is the maximum Ethernet frame without VLAN tags.
+pub(super) const MAX_PKT_SIZE: usize = 1514;
+
+const PKTGEN_MAGIC: u32 = 0xbe9be955;
+const ETH_P_IP: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+/// The "don't fragment" flag, which makes the identification field unused.
+const IP_DF: u16 = 0x4000;
+/// The default TTL of pktgen, which is the same as Linux.
+const PKTGEN_TTL: u8 = 32;
+
+/// Builds a packet according to the configuration.
+///
+/// The sequence number and the timestamp are filled by [`stamp_packet`].
+pub(super) fn build_packet(config: &PktgenConfig, src_mac: [u8; 6]) -> Vec<u8> {
+    let pkt_size = config.pkt_size;
+    debug_assert!((MIN_PKT_SIZE..=MAX_PKT_SIZE).contains(&pkt_size));
+
+    let mut packet = vec![0u8; pkt_size];
+
+    let eth = &mut packet[..ETH_HEADER_LEN];
+    eth[0..6].copy_from_slice(&config.dst_mac);
+    eth[6..12].copy_from_slice(&config.src_mac.unwrap_or(src_mac));
+    eth[12..14].copy_from_slice(&ETH_P_IP.to_be_bytes());
+
+    let ip_len = (pkt_size - ETH_HEADER_LEN) as u16;
+    let ip = &mut packet[ETH_HEADER_LEN..ETH_HEADER_LEN + IPV4_HEADER_LEN];
+    ip[0] = 0x45;
+    ip[2..4].copy_from_slice(&ip_len.to_be_bytes());
+    ip[6..8].copy_from_slice(&IP_DF.to_be_bytes());
+    ip[8] = PKTGEN_TTL;
+    ip[9] = IPPROTO_UDP;
+    ip[12..16].copy_from_slice(&config.src_ip.octets());
+    ip[16..20].copy_from_slice(&config.dst_ip.octets());
+    let checksum = ipv4_checksum(ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    // The UDP checksum is optional for IPv4, so it is left as zero.
+    let udp_len = ip_len - IPV4_HEADER_LEN as u16;
+    let udp = &mut packet[ETH_HEADER_LEN + IPV4_HEADER_LEN..PKTGEN_HEADER_OFFSET];
+    udp[0..2].copy_from_slice(&config.udp_src.to_be_bytes());
+    udp[2..4].copy_from_slice(&config.udp_dst.to_be_bytes());
+    udp[4..6].copy_from_slice(&udp_len.to_be_bytes());
+
+    packet[PKTGEN_HEADER_OFFSET..PKTGEN_HEADER_OFFSET + 4]
+        .copy_from_slice(&PKTGEN_MAGIC.to_be_bytes());
+
+    packet
+}
+
+/// Fills the sequence number and the timestamp in the pktgen header.
+pub(super) fn stamp_packet(packet: &mut [u8], seq_num: u32, timestamp: Duration) {
+    let header = &mut packet[PKTGEN_HEADER_OFFSET..PKTGEN_HEADER_OFFSET + PKTGEN_HEADER_LEN];
+    header[4..8].copy_from_slice(&seq_num.to_be_bytes());
+    header[8..12].copy_from_slice(&(timestamp.as_secs() as u32).to_be_bytes());
+    header[12..16].copy_from_slice(&timestamp.subsec_micros().to_be_bytes());
+}
+
+/// Computes the Internet checksum of the IPv4 header.
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::net::Ipv4Addr;
+
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn packet_layout() {
+        let config = PktgenConfig {
+            pkt_size: 64,
+            dst_mac: [0x52, 0x54, 0, 0x12, 0x34, 0x56],
+            src_ip: Ipv4Addr::new(10, 0, 2, 15),
+            dst_ip: Ipv4Addr::new(10, 0, 2, 2),
+            ..Default::default()
+        };
+        let mut packet = build_packet(&config, [2, 0, 0, 0, 0, 1]);
+        stamp_packet(&mut packet, 7, Duration::new(3, 4000));
+
+        assert_eq!(packet.len(), 64);
+        assert_eq!(&packet[0..6], &config.dst_mac);
+        assert_eq!(&packet[6..12], &[2, 0, 0, 0, 0, 1]);
+        assert_eq!(&packet[12..14], &[0x08, 0x00]);
+
+        let ip = &packet[ETH_HEADER_LEN..ETH_HEADER_LEN + IPV4_HEADER_LEN];
+        assert_eq!(u16::from_be_bytes([ip[2], ip[3]]), 50);
+        // The checksum of a header with a valid checksum is zero.
+        assert_eq!(ipv4_checksum(ip), 0);
+
+        let header = &packet[PKTGEN_HEADER_OFFSET..];
+        assert_eq!(&header[0..4], &[0xbe, 0x9b, 0xe9, 0x55]);
+        assert_eq!(&header[4..8], &[0, 0, 0, 7]);
+        assert_eq!(&header[8..12], &[0, 0, 0, 3]);
+        assert_eq!(&header[12..16], &[0, 0, 0, 4]);
+    }
+}
@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The forwarding information base (FIB), which decides how packets are routed.
+//!
+//! Routes are organized into routing tables. A lookup consults the policy rules in the order of
+//! their priorities, and each matching rule either selects a table or rejects the packet. In a
+//! table, the route with the longest matching prefix wins, and ties are broken by the metrics.
+//!
+//! Besides the routes added from user space, the tables contain routes that are implied by the
+//! ifaces: the routes to the subnets of the addresses (in the main table), the routes to the
+//! addresses themselves (in the local table), and the routes via gateways learned by the ifaces
+//! (e.g., from Router Advertisement messages, in the main table).
+//!
+//! The ifaces resolve the next hops themselves, so the gateways of the added routes are
+//! installed to the output ifaces. If multiple routes via the same iface share a destination,
+//! the gateway of the one with the lowest metric is installed.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man8/ip-rule.8.html>.
+
+use aster_bigtcp::{
+    iface::Route,
+    wire::{IpAddress, IpCidr},
+};
+
+pub use self::{
+    rule::{FibRule, RuleAction},
+    table::{
+        iface_has_addr, iface_subnets, max_prefix_len, FibRoute, RouteTable, RouteType,
+        IPV6_DEFAULT_METRIC, TABLE_DEFAULT, TABLE_LOCAL, TABLE_MAIN,
+    },
+};
+use crate::{
+    net::iface::{iter_all_ifaces, Iface},
+    prelude::*,
+};
+
+mod rule;
+mod table;
+
+// Routing protocols, which indicate the origins of routes.
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L283>.
+pub const RTPROT_KERNEL: u8 = 2;
+pub const RTPROT_BOOT: u8 = 3;
+pub const RTPROT_RA: u8 = 9;
+pub const RTPROT_DHCP: u8 = 16;
+
+/// The routing tables and the policy rules.
+#[derive(Debug)]
+pub struct Fib {
+    tables: BTreeMap<u32, RouteTable>,
+    rules: Vec<FibRule>,
+    /// The destinations whose gateways are installed to the ifaces, keyed by the iface indexes.
+    installed: BTreeMap<u32, Vec<IpCidr>>,
+}
+
+/// The result of a route lookup.
+#[derive(Clone)]
+pub struct RouteResult {
+    /// The route that is selected.
+    pub route: FibRoute,
+    /// The table that contains the route.
+    pub table: u32,
+    /// The output iface.
+    pub iface: Arc<Iface>,
+}
+
+impl Fib {
+    const fn new() -> Self {
+        Self {
+            tables: BTreeMap::new(),
+            rules: Vec::new(),
+            installed: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the IDs of the tables that have routes added from user space.
+    pub fn table_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tables
+            .iter()
+            .filter(|(_, table)| !table.routes().is_empty())
+            .map(|(id, _)| *id)
+    }
+
+    /// Returns the routes added to the table from user space.
+    pub fn routes(&self, table: u32) -> &[FibRoute] {
+        self.tables
+            .get(&table)
+            .map(RouteTable::routes)
+            .unwrap_or_default()
+    }
+
+    /// Returns the table, which is created if it does not exist.
+    pub fn table_mut(&mut self, table: u32) -> &mut RouteTable {
+        self.tables.entry(table).or_default()
+    }
+
+    /// Returns all routes in the table, including the routes implied by the ifaces.
+    pub fn all_routes(&self, table: u32) -> Vec<FibRoute> {
+        let mut routes = Vec::new();
+
+        for iface in iter_all_ifaces() {
+            routes.extend(
+                table::implicit_routes(&iface)
+                    .filter(|(id, _)| *id == table)
+                    .map(|(_, route)| route),
+            );
+            if table == TABLE_MAIN {
+                routes.extend(self.learned_routes(&iface));
+            }
+        }
+
+        routes.extend(
+            self.routes(table)
+                .iter()
+                .filter(|route| route.oif.is_none_or(iface_exists))
+                .cloned(),
+        );
+
+        routes
+    }
+
+    /// Returns the routes via gateways that are learned by the iface.
+    fn learned_routes(&self, iface: &Arc<Iface>) -> impl Iterator<Item = FibRoute> {
+        let index = iface.index();
+        let installed = self.installed.get(&index).cloned().unwrap_or_default();
+
+        iface
+            .routes()
+            .into_iter()
+            .filter(move |route| !installed.contains(&route.cidr))
+            .map(move |route| FibRoute::new_learned(&route, index))
+    }
+
+    /// Removes the first route learned by the ifaces that satisfies the predicate.
+    ///
+    /// This method returns `false` if no such route exists.
+    pub fn remove_learned_route(&self, pred: impl Fn(&FibRoute) -> bool) -> bool {
+        for iface in iter_all_ifaces() {
+            if let Some(route) = self.learned_routes(&iface).find(&pred) {
+                iface.remove_route(&route.dst);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the policy rules in the order of their priorities.
+    pub fn rules(&self) -> &[FibRule] {
+        &self.rules
+    }
+
+    /// Adds a policy rule after the existing rules with the same or higher priorities.
+    pub fn add_rule(&mut self, rule: FibRule) {
+        let pos = self
+            .rules
+            .iter()
+            .position(|other| other.priority > rule.priority)
+            .unwrap_or(self.rules.len());
+        self.rules.insert(pos, rule);
+    }
+
+    /// Removes the first policy rule that satisfies the predicate.
+    ///
+    /// This method returns `false` if no rule satisfies the predicate.
+    pub fn remove_rule(&mut self, pred: impl Fn(&FibRule) -> bool) -> bool {
+        let Some(pos) = self.rules.iter().position(pred) else {
+            return false;
+        };
+        self.rules.remove(pos);
+        true
+    }
+
+    /// Returns the priority of a new rule whose priority is not specified.
+    ///
+    /// Like Linux, the priority is one less than the priority of the second rule, which usually
+    /// follows the rule that looks up the local table.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/fib_rules.c#L46>.
+    pub fn default_rule_priority(&self, is_ipv6: bool) -> u32 {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_ipv6 == is_ipv6)
+            .nth(1)
+            .map_or(0, |rule| rule.priority.saturating_sub(1))
+    }
+
+    /// Looks up the route to the destination.
+    ///
+    /// If the source address is specified, the rules that select the source addresses will be
+    /// taken into account.
+    pub fn lookup(&self, dst: &IpAddress, src: Option<&IpAddress>) -> Result<RouteResult> {
+        let is_ipv6 = matches!(dst, IpAddress::Ipv6(_));
+
+        for rule in self.rules.iter().filter(|rule| rule.is_ipv6 == is_ipv6) {
+            if !rule.matches(dst, src) {
+                continue;
+            }
+
+            let table = match rule.action {
+                RuleAction::ToTable(table) => table,
+                RuleAction::Nop => continue,
+                RuleAction::Blackhole => {
+                    return_errno_with_message!(Errno::EINVAL, "the rule drops the packets")
+                }
+                RuleAction::Unreachable => {
+                    return_errno_with_message!(Errno::ENETUNREACH, "the rule rejects the packets")
+                }
+                RuleAction::Prohibit => {
+                    return_errno_with_message!(Errno::EACCES, "the rule prohibits the packets")
+                }
+            };
+
+            let Some(route) = self.lookup_table(table, dst) else {
+                continue;
+            };
+            match route.type_ {
+                RouteType::Unicast | RouteType::Local => (),
+                RouteType::Throw => continue,
+                RouteType::Blackhole => {
+                    return_errno_with_message!(Errno::EINVAL, "the route drops the packets")
+                }
+                RouteType::Unreachable => {
+                    return_errno_with_message!(Errno::EHOSTUNREACH, "the route rejects the packets")
+                }
+                RouteType::Prohibit => {
+                    return_errno_with_message!(Errno::EACCES, "the route prohibits the packets")
+                }
+            }
+
+            // Unicast and local routes always have output ifaces, which exist because they are
+            // checked in `Self::all_routes`.
+            let Some(iface) = route.oif.and_then(find_iface) else {
+                continue;
+            };
+            return Ok(RouteResult {
+                route,
+                table,
+                iface,
+            });
+        }
+
+        return_errno_with_message!(Errno::ENETUNREACH, "there is no route to the destination")
+    }
+
+    /// Looks up the route to the destination in the table.
+    fn lookup_table(&self, table: u32, dst: &IpAddress) -> Option<FibRoute> {
+        // `min_by_key` returns the first route if several routes are equally preferred.
+        self.all_routes(table)
+            .into_iter()
+            .filter(|route| route.dst.contains_addr(dst))
+            .min_by_key(|route| (u8::MAX - route.dst.prefix_len(), route.metric))
+    }
+
+    /// Installs the gateways of the routes to the output ifaces.
+    fn install_gateways(&mut self) {
+        let ifaces: Vec<_> = iter_all_ifaces().collect();
+
+        for iface in ifaces.iter() {
+            let index = iface.index();
+
+            // The preferred gateway and its metric for each destination.
+            let mut gateways: Vec<(IpCidr, IpAddress, u32)> = Vec::new();
+            let routes = self
+                .tables
+                .values()
+                .flat_map(RouteTable::routes)
+                .filter(|route| route.type_ == RouteType::Unicast && route.oif == Some(index));
+            for route in routes {
+                let Some(gateway) = route.gateway else {
+                    continue;
+                };
+                match gateways.iter_mut().find(|(dst, _, _)| *dst == route.dst) {
+                    Some(entry) if entry.2 > route.metric => {
+                        *entry = (route.dst, gateway, route.metric)
+                    }
+                    Some(_) => (),
+                    None => gateways.push((route.dst, gateway, route.metric)),
+                }
+            }
+
+            let installed = self.installed.entry(index).or_default();
+            for dst in installed.iter() {
+                if gateways.iter().all(|(other, _, _)| other != dst) {
+                    iface.remove_route(dst);
+                }
+            }
+            installed.clear();
+
+            for (dst, gateway, _) in gateways {
+                let route = Route {
+                    cidr: dst,
+                    via_router: gateway,
+                    preferred_until: None,
+                    expires_at: None,
+                };
+                if iface.add_route(route) {
+                    installed.push(dst);
+                } else {
+                    warn!("the interface {} has too many routes", iface.name());
+                }
+            }
+        }
+
+        self.installed
+            .retain(|index, _| ifaces.iter().any(|iface| iface.index() == *index));
+    }
+}
+
+fn find_iface(index: u32) -> Option<Arc<Iface>> {
+    iter_all_ifaces().find(|iface| iface.index() == index)
+}
+
+fn iface_exists(index: u32) -> bool {
+    iter_all_ifaces().any(|iface| iface.index() == index)
+}
+
+static FIB: Mutex<Fib> = Mutex::new(Fib::new());
+
+pub fn init() {
+    let mut fib = FIB.lock();
+    for rule in rule::default_rules() {
+        fib.add_rule(rule);
+    }
+}
+
+/// Looks up the route to the destination.
+///
+/// See [`Fib::lookup`] for details.
+pub fn lookup(dst: &IpAddress, src: Option<&IpAddress>) -> Result<RouteResult> {
+    FIB.lock().lookup(dst, src)
+}
+
+/// Calls `f` with the FIB.
+pub fn with_fib<R>(f: impl FnOnce(&Fib) -> R) -> R {
+    f(&FIB.lock())
+}
+
+/// Calls `f` to change the FIB.
+///
+/// After `f` returns, the gateways of the routes are installed to the ifaces, even if `f` fails.
+pub fn modify_fib<R>(f: impl FnOnce(&mut Fib) -> Result<R>) -> Result<R> {
+    let mut fib = FIB.lock();
+    let res = f(&mut fib);
+    fib.install_gateways();
+    res
+}
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::wire::{IpAddress, IpCidr};
+
+use super::{RTPROT_KERNEL, TABLE_DEFAULT, TABLE_LOCAL, TABLE_MAIN};
+use crate::prelude::*;
+
+/// A policy rule, which decides how to route the packets that match its selectors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FibRule {
+    pub is_ipv6: bool,
+    /// The priority, where lower values are consulted first.
+    pub priority: u32,
+    /// The source addresses that are selected, or `None` to select all.
+    pub src: Option<IpCidr>,
+    /// The destination addresses that are selected, or `None` to select all.
+    pub dst: Option<IpCidr>,
+    /// Whether the rule applies to the packets that are _not_ selected.
+    pub invert: bool,
+    pub action: RuleAction,
+    /// The routing protocol, which indicates the origin of the rule.
+    pub protocol: u8,
+}
+
+/// The action of a policy rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Looks up the routing table with the ID.
+    ToTable(u32),
+    /// Does nothing and continues with the next rule.
+    Nop,
+    /// Silently discards the packets.
+    Blackhole,
+    /// Rejects the packets as if the network is unreachable.
+    Unreachable,
+    /// Rejects the packets as administratively prohibited.
+    Prohibit,
+}
+
+impl FibRule {
+    /// Returns whether the rule applies to the packet.
+    ///
+    /// If the source address is unknown, the rules that select specific source addresses do not
+    /// apply (unless they are inverted).
+    pub(super) fn matches(&self, dst: &IpAddress, src: Option<&IpAddress>) -> bool {
+        let is_selected = self.dst.is_none_or(|cidr| cidr.contains_addr(dst))
+            && self
+                .src
+                .is_none_or(|cidr| src.is_some_and(|src| cidr.contains_addr(src)));
+        is_selected != self.invert
+    }
+
+    fn new_default(is_ipv6: bool, priority: u32, table: u32) -> Self {
+        Self {
+            is_ipv6,
+            priority,
+            src: None,
+            dst: None,
+            invert: false,
+            action: RuleAction::ToTable(table),
+            protocol: RTPROT_KERNEL,
+        }
+    }
+}
+
+/// Returns the rules that exist at boot time.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/fib_rules.c#L405> and
+/// <https://elixir.bootlin.com/linux/v6.13/source/net/ipv6/fib6_rules.c#L458>.
+pub(super) fn default_rules() -> [FibRule; 5] {
+    [
+        FibRule::new_default(false, 0, TABLE_LOCAL),
+        FibRule::new_default(false, 0x7FFE, TABLE_MAIN),
+        FibRule::new_default(false, 0x7FFF, TABLE_DEFAULT),
+        FibRule::new_default(true, 0, TABLE_LOCAL),
+        FibRule::new_default(true, 0x7FFE, TABLE_MAIN),
+    ]
+}
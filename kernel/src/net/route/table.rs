@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::{
+    iface::Route,
+    wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address},
+};
+
+use super::{RTPROT_BOOT, RTPROT_KERNEL, RTPROT_RA};
+use crate::{net::iface::Iface, prelude::*};
+
+// Reserved routing table IDs.
+// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/rtnetlink.h#L353>.
+pub const TABLE_DEFAULT: u32 = 253;
+pub const TABLE_MAIN: u32 = 254;
+pub const TABLE_LOCAL: u32 = 255;
+
+/// The metric of the routes to the subnets of IPv6 addresses.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/ip6_route.h#L33>.
+const IPV6_PREFIX_METRIC: u32 = 256;
+/// The metric of the IPv6 routes via gateways if the metric is not specified.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/net/ip6_route.h#L32>.
+pub const IPV6_DEFAULT_METRIC: u32 = 1024;
+
+/// A route in a routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FibRoute {
+    pub dst: IpCidr,
+    pub type_: RouteType,
+    /// The gateway, or `None` if the destination is directly reachable.
+    pub gateway: Option<IpAddress>,
+    /// The index of the output iface.
+    ///
+    /// This is `None` only for the routes that reject the packets.
+    pub oif: Option<u32>,
+    /// The source address that is preferred to reach the destination.
+    pub pref_src: Option<IpAddress>,
+    /// The metric (i.e., the priority), where lower values are preferred.
+    pub metric: u32,
+    /// The routing protocol, which indicates the origin of the route.
+    pub protocol: u8,
+}
+
+/// The type of a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteType {
+    /// The destination is reachable via a gateway or directly.
+    Unicast,
+    /// The destination is a local address.
+    Local,
+    /// The packets are silently discarded.
+    Blackhole,
+    /// The destination is unreachable.
+    Unreachable,
+    /// The destination is administratively prohibited.
+    Prohibit,
+    /// The lookup continues with the next policy rule.
+    Throw,
+}
+
+/// A routing table, which holds the routes added from user space.
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    routes: Vec<FibRoute>,
+}
+
+impl FibRoute {
+    /// Creates a route from the route via a gateway that is learned by the iface.
+    pub(super) fn new_learned(route: &Route, oif: u32) -> Self {
+        let (metric, protocol) = match route.cidr {
+            IpCidr::Ipv4(_) => (0, RTPROT_BOOT),
+            IpCidr::Ipv6(_) => (IPV6_DEFAULT_METRIC, RTPROT_RA),
+        };
+
+        Self {
+            dst: route.cidr,
+            type_: RouteType::Unicast,
+            gateway: Some(route.via_router),
+            oif: Some(oif),
+            pref_src: None,
+            metric,
+            protocol,
+        }
+    }
+
+    fn new_implicit(dst: IpCidr, type_: RouteType, oif: u32, pref_src: Option<IpAddress>) -> Self {
+        let metric = match dst {
+            IpCidr::Ipv6(_) if type_ == RouteType::Unicast => IPV6_PREFIX_METRIC,
+            _ => 0,
+        };
+
+        Self {
+            dst,
+            type_,
+            gateway: None,
+            oif: Some(oif),
+            pref_src,
+            metric,
+            protocol: RTPROT_KERNEL,
+        }
+    }
+}
+
+impl RouteTable {
+    pub fn routes(&self) -> &[FibRoute] {
+        &self.routes
+    }
+
+    pub fn routes_mut(&mut self) -> &mut Vec<FibRoute> {
+        &mut self.routes
+    }
+}
+
+/// Returns the routes that are implicitly created by the kernel when adding addresses to the
+/// iface, along with the IDs of their tables.
+pub(super) fn implicit_routes(iface: &Iface) -> impl Iterator<Item = (u32, FibRoute)> {
+    let index = iface.index();
+    let mut routes = Vec::new();
+
+    // Routes to the addresses themselves.
+    for ip_cidr in iface_addrs(iface) {
+        let addr = ip_cidr.address();
+        let pref_src = matches!(addr, IpAddress::Ipv4(_)).then_some(addr);
+        let dst = IpCidr::new(addr, max_prefix_len(&addr));
+        let route = FibRoute::new_implicit(dst, RouteType::Local, index, pref_src);
+        routes.push((TABLE_LOCAL, route));
+    }
+
+    // Routes to the subnets.
+    let mut subnets = Vec::new();
+    for ip_cidr in iface_addrs(iface) {
+        let subnet = subnet_of(&ip_cidr);
+        if subnets.contains(&subnet) {
+            continue;
+        }
+        subnets.push(subnet);
+
+        let route = match ip_cidr {
+            // Like Linux, routes to loopback addresses are in the local table.
+            IpCidr::Ipv4(ipv4_cidr) if ipv4_cidr.address().is_loopback() => (
+                TABLE_LOCAL,
+                FibRoute::new_implicit(subnet, RouteType::Local, index, Some(ip_cidr.address())),
+            ),
+            IpCidr::Ipv4(_) => (
+                TABLE_MAIN,
+                FibRoute::new_implicit(subnet, RouteType::Unicast, index, Some(ip_cidr.address())),
+            ),
+            IpCidr::Ipv6(_) => (
+                TABLE_MAIN,
+                FibRoute::new_implicit(subnet, RouteType::Unicast, index, None),
+            ),
+        };
+        routes.push(route);
+    }
+
+    routes.into_iter()
+}
+
+fn iface_addrs(iface: &Iface) -> impl Iterator<Item = IpCidr> {
+    let ipv4_addrs = iface.ipv4_addrs().into_iter().map(IpCidr::Ipv4);
+    let ipv6_addrs = iface.ipv6_addrs().into_iter().map(IpCidr::Ipv6);
+    ipv4_addrs.chain(ipv6_addrs)
+}
+
+/// Returns the subnets of the addresses of the iface.
+pub fn iface_subnets(iface: &Iface) -> impl Iterator<Item = IpCidr> {
+    iface_addrs(iface).map(|ip_cidr| subnet_of(&ip_cidr))
+}
+
+/// Returns whether the address is one of the addresses of the iface.
+pub fn iface_has_addr(iface: &Iface, addr: &IpAddress) -> bool {
+    iface_addrs(iface).any(|ip_cidr| ip_cidr.address() == *addr)
+}
+
+/// Returns the subnet that contains the address.
+fn subnet_of(ip_cidr: &IpCidr) -> IpCidr {
+    let prefix_len = ip_cidr.prefix_len();
+
+    let network = match ip_cidr.address() {
+        IpAddress::Ipv4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv4(Ipv4Address::from(u32::from(addr) & mask))
+        }
+        IpAddress::Ipv6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv6(Ipv6Address::from(u128::from(addr) & mask))
+        }
+    };
+
+    IpCidr::new(network, prefix_len)
+}
+
+/// Returns the prefix length of a route to a single address.
+pub fn max_prefix_len(addr: &IpAddress) -> u8 {
+    match addr {
+        IpAddress::Ipv4(_) => 32,
+        IpAddress::Ipv6(_) => 128,
+    }
+}
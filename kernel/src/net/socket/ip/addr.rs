@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use aster_bigtcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
+use super::options::Ipv6OptionSet;
 use crate::{net::socket::SocketAddr, prelude::*, return_errno_with_message};
 
 impl TryFrom<SocketAddr> for IpEndpoint {
@@ -10,6 +11,7 @@ impl TryFrom<SocketAddr> for IpEndpoint {
     fn try_from(value: SocketAddr) -> Result<Self> {
         match value {
             SocketAddr::IPv4(addr, port) => Ok(IpEndpoint::new(addr.into(), port)),
+            SocketAddr::IPv6(addr, port) => Ok(IpEndpoint::new(addr.into(), port)),
             _ => return_errno_with_message!(
                 Errno::EAFNOSUPPORT,
                 "the address is in an unsupported address family"
@@ -23,15 +25,87 @@ impl From<IpEndpoint> for SocketAddr {
         let port = endpoint.port;
         match endpoint.addr {
             IpAddress::Ipv4(addr) => SocketAddr::IPv4(addr, port),
-            // TODO: support IPv6
+            IpAddress::Ipv6(addr) => SocketAddr::IPv6(addr, port),
         }
     }
 }
 
-/// A local endpoint, which indicates that the local endpoint is unspecified.
+/// Converts a socket address to a local endpoint for `bind`.
+///
+/// `ipv6` should be the IPv6-level options if the socket is in the `AF_INET6` domain, or `None` if
+/// it is in the `AF_INET` domain.
+pub(super) fn local_endpoint_from(
+    socket_addr: SocketAddr,
+    ipv6: Option<&Ipv6OptionSet>,
+) -> Result<IpEndpoint> {
+    // Linux reports `EINVAL` if an IPv6-only socket is bound to an IPv4-mapped IPv6 address.
+    endpoint_from(socket_addr, ipv6, Errno::EINVAL)
+}
+
+/// Converts a socket address to a remote endpoint for `connect` or `sendto`.
+///
+/// `ipv6` should be the IPv6-level options if the socket is in the `AF_INET6` domain, or `None` if
+/// it is in the `AF_INET` domain.
+pub(super) fn remote_endpoint_from(
+    socket_addr: SocketAddr,
+    ipv6: Option<&Ipv6OptionSet>,
+) -> Result<IpEndpoint> {
+    // Linux reports `ENETUNREACH` if an IPv6-only socket is connected to an IPv4-mapped IPv6
+    // address.
+    endpoint_from(socket_addr, ipv6, Errno::ENETUNREACH)
+}
+
+fn endpoint_from(
+    socket_addr: SocketAddr,
+    ipv6: Option<&Ipv6OptionSet>,
+    v4mapped_errno: Errno,
+) -> Result<IpEndpoint> {
+    match (socket_addr, ipv6) {
+        (SocketAddr::IPv4(addr, port), None) => Ok(IpEndpoint::new(addr.into(), port)),
+        (SocketAddr::IPv6(addr, port), Some(ipv6)) => {
+            let Some(ipv4_addr) = addr.to_ipv4_mapped() else {
+                return Ok(IpEndpoint::new(addr.into(), port));
+            };
+            if ipv6.v6only() {
+                return_errno_with_message!(
+                    v4mapped_errno,
+                    "IPv4-mapped addresses are not allowed for IPv6-only sockets"
+                );
+            }
+            Ok(IpEndpoint::new(ipv4_addr.into(), port))
+        }
+        // Linux checks the address length first. An IPv4 socket address is always shorter than
+        // the minimum length required by IPv6 sockets.
+        (SocketAddr::IPv4(..), Some(_)) => {
+            return_errno_with_message!(Errno::EINVAL, "the socket address length is too small")
+        }
+        _ => return_errno_with_message!(
+            Errno::EAFNOSUPPORT,
+            "the address is in an unsupported address family"
+        ),
+    }
+}
+
+/// Converts an endpoint to a socket address that will be reported to the user space.
+///
+/// For sockets in the `AF_INET6` domain, IPv4 endpoints are reported as IPv4-mapped IPv6
+/// addresses.
+pub(super) fn socket_addr_from(endpoint: IpEndpoint, ipv6: Option<&Ipv6OptionSet>) -> SocketAddr {
+    match (endpoint.addr, ipv6) {
+        (IpAddress::Ipv4(addr), Some(_)) => SocketAddr::IPv6(addr.to_ipv6_mapped(), endpoint.port),
+        _ => endpoint.into(),
+    }
+}
+
+/// Returns a local endpoint, which indicates that the local endpoint is unspecified.
 ///
 /// According to the Linux man pages and the Linux implementation, `getsockname()` will _not_ fail
 /// even if the socket is unbound. Instead, it will return an unspecified socket address. This
 /// unspecified endpoint helps with that.
-pub(super) const UNSPECIFIED_LOCAL_ENDPOINT: IpEndpoint =
-    IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 0);
+pub(super) fn unspecified_local_endpoint(ipv6: Option<&Ipv6OptionSet>) -> IpEndpoint {
+    if ipv6.is_some() {
+        IpEndpoint::new(IpAddress::Ipv6(Ipv6Address::UNSPECIFIED), 0)
+    } else {
+        IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 0)
+    }
+}
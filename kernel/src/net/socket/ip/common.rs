@@ -3,48 +3,29 @@
 use aster_bigtcp::{
     errors::BindError,
     iface::BindPortConfig,
+    socket::IcmpError,
     wire::{IpAddress, IpEndpoint},
 };
 
 use crate::{
-    net::iface::{iter_all_ifaces, loopback_iface, virtio_iface, BoundPort, Iface},
+    net::{
+        iface::{iter_all_ifaces, BoundPort, Iface},
+        route::{self, RouteResult},
+    },
     prelude::*,
 };
 
 pub(super) fn get_iface_to_bind(ip_addr: &IpAddress) -> Option<Arc<Iface>> {
-    let IpAddress::Ipv4(ipv4_addr) = ip_addr;
-    iter_all_ifaces()
-        .find(|iface| {
-            if let Some(iface_ipv4_addr) = iface.ipv4_addr() {
-                iface_ipv4_addr == *ipv4_addr
-            } else {
-                false
-            }
-        })
-        .map(Clone::clone)
+    iter_all_ifaces().find(|iface| iface_has_addr(iface, ip_addr))
 }
 
-/// Get a suitable iface to deal with sendto/connect request if the socket is not bound to an iface.
-/// If the remote address is the same as that of some iface, we will use the iface.
-/// Otherwise, we will use a default interface.
-fn get_ephemeral_iface(remote_ip_addr: &IpAddress) -> Arc<Iface> {
-    let IpAddress::Ipv4(remote_ipv4_addr) = remote_ip_addr;
-    if let Some(iface) = iter_all_ifaces().find(|iface| {
-        if let Some(iface_ipv4_addr) = iface.ipv4_addr() {
-            iface_ipv4_addr == *remote_ipv4_addr
-        } else {
-            false
-        }
-    }) {
-        return iface.clone();
-    }
-
-    // FIXME: Instead of hardcoding the rules here, we should choose the
-    // default interface according to the routing table.
-    if let Some(virtio_iface) = virtio_iface() {
-        virtio_iface.clone()
-    } else {
-        loopback_iface().clone()
+fn iface_has_addr(iface: &Iface, ip_addr: &IpAddress) -> bool {
+    match ip_addr {
+        IpAddress::Ipv4(ipv4_addr) => iface.ipv4_addr() == Some(*ipv4_addr),
+        IpAddress::Ipv6(ipv6_addr) => iface
+            .ipv6_addrs()
+            .iter()
+            .any(|ipv6_cidr| ipv6_cidr.address() == *ipv6_addr),
     }
 }
 
@@ -61,7 +42,7 @@ pub(super) fn bind_port(endpoint: &IpEndpoint, can_reuse: bool) -> Result<BoundP
 
     let bind_port_config = BindPortConfig::new(endpoint.port, can_reuse);
 
-    Ok(iface.bind(bind_port_config)?)
+    Ok(iface.bind(endpoint.addr, bind_port_config)?)
 }
 
 impl From<BindError> for Error {
@@ -77,8 +58,59 @@ impl From<BindError> for Error {
     }
 }
 
-pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> IpEndpoint {
-    let iface = get_ephemeral_iface(&remote_endpoint.addr);
-    let ip_addr = iface.ipv4_addr().unwrap();
-    IpEndpoint::new(IpAddress::Ipv4(ip_addr), 0)
+/// Gets a suitable local endpoint to deal with sendto/connect requests if the socket is not
+/// bound.
+///
+/// The local address is chosen according to the route to the remote address.
+pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> Result<IpEndpoint> {
+    let RouteResult { route, iface, .. } = route::lookup(&remote_endpoint.addr, None)?;
+
+    let ip_addr = match (&remote_endpoint.addr, route.pref_src) {
+        (_, Some(pref_src)) => Some(pref_src),
+        (IpAddress::Ipv4(_), None) => iface.ipv4_addr().map(IpAddress::Ipv4),
+        (IpAddress::Ipv6(ipv6_addr), None) => iface.ipv6_src_addr(ipv6_addr).map(IpAddress::Ipv6),
+    };
+
+    let Some(ip_addr) = ip_addr else {
+        return_errno_with_message!(
+            Errno::ENETUNREACH,
+            "the interface has no address in the same address family"
+        );
+    };
+    Ok(IpEndpoint::new(ip_addr, 0))
+}
+
+/// Converts an ICMP error message to the error code that is reported to the socket.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/icmp.c#L113> and
+/// <https://elixir.bootlin.com/linux/v6.13/source/net/ipv6/icmp.c#L1112>.
+pub(super) fn icmp_error_to_errno(error: &IcmpError) -> Errno {
+    if error.is_too_big() {
+        return Errno::EMSGSIZE;
+    }
+
+    if error.is_ipv6 {
+        match (error.type_, error.code) {
+            // Destination unreachable
+            (1, 0) => Errno::ENETUNREACH,
+            (1, 1 | 5 | 6) => Errno::EACCES,
+            (1, 4) => Errno::ECONNREFUSED,
+            // Parameter problem
+            (4, _) => Errno::EPROTO,
+            _ => Errno::EHOSTUNREACH,
+        }
+    } else {
+        match (error.type_, error.code) {
+            // Destination unreachable
+            (3, 0 | 6 | 9 | 11) => Errno::ENETUNREACH,
+            (3, 2) => Errno::ENOPROTOOPT,
+            (3, 3) => Errno::ECONNREFUSED,
+            (3, 5) => Errno::EOPNOTSUPP,
+            (3, 7) => Errno::EHOSTDOWN,
+            (3, 8) => Errno::ENONET,
+            // Parameter problem
+            (12, _) => Errno::EPROTO,
+            _ => Errno::EHOSTUNREACH,
+        }
+    }
 }
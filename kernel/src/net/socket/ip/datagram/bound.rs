@@ -1,27 +1,40 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
 use aster_bigtcp::{
-    errors::udp::{RecvError, SendError},
+    errors::{icmp, udp},
+    socket::{IcmpError, ICMP_SEND_BUF_LEN},
     wire::IpEndpoint,
 };
 
 use crate::{
     events::IoEvents,
     net::{
-        iface::{Iface, UdpSocket},
-        socket::util::{datagram_common, send_recv_flags::SendRecvFlags},
+        iface::{IcmpSocket, Iface, UdpSocket},
+        socket::util::{
+            datagram_common,
+            error_queue::{ErrorQueue, TimestampType},
+            send_recv_flags::SendRecvFlags,
+        },
     },
     prelude::*,
     util::{MultiRead, MultiWrite},
 };
 
 pub(super) struct BoundDatagram {
-    bound_socket: UdpSocket,
+    bound_socket: BoundSocket,
     remote_endpoint: Option<IpEndpoint>,
 }
 
+/// A bound UDP socket or a bound ICMP socket (i.e., a ping socket).
+pub(super) enum BoundSocket {
+    Udp(UdpSocket),
+    Icmp(IcmpSocket),
+}
+
 impl BoundDatagram {
-    pub(super) fn new(bound_socket: UdpSocket) -> Self {
+    pub(super) fn new(bound_socket: BoundSocket) -> Self {
         Self {
             bound_socket,
             remote_endpoint: None,
@@ -29,57 +42,86 @@ impl BoundDatagram {
     }
 
     pub(super) fn iface(&self) -> &Arc<Iface> {
-        self.bound_socket.iface()
+        match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket.iface(),
+            BoundSocket::Icmp(icmp_socket) => icmp_socket.iface(),
+        }
     }
-}
 
-impl datagram_common::Bound for BoundDatagram {
-    type Endpoint = IpEndpoint;
-
-    fn local_endpoint(&self) -> Self::Endpoint {
-        self.bound_socket.local_endpoint().unwrap()
-    }
+    /// Receives a packet along with the time at which the packet was received.
+    ///
+    /// For ping sockets, the packet is an ICMP echo reply (including the ICMP header), the port
+    /// of the returned endpoint is always zero, and no timestamp is available.
+    pub(super) fn try_recv_with_timestamp(
+        &self,
+        writer: &mut dyn MultiWrite,
+    ) -> Result<(usize, IpEndpoint, Option<Duration>)> {
+        let udp_socket = match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket,
+            BoundSocket::Icmp(icmp_socket) => {
+                return Self::try_recv_icmp(icmp_socket, writer)
+                    .map(|(recv_bytes, endpoint)| (recv_bytes, endpoint, None));
+            }
+        };
 
-    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
-        self.remote_endpoint.as_ref()
-    }
+        let result = udp_socket.recv(|packet, udp_metadata, timestamp| {
+            let copied_res = writer.write(&mut VmReader::from(packet));
+            let endpoint = udp_metadata.endpoint;
+            (copied_res, endpoint, timestamp)
+        });
 
-    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
-        self.remote_endpoint = Some(*endpoint)
+        match result {
+            Ok((Ok(res), endpoint, timestamp)) => Ok((res, endpoint, timestamp)),
+            Ok((Err(e), _, _)) => Err(e),
+            Err(udp::RecvError::Exhausted) => {
+                return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty")
+            }
+            Err(udp::RecvError::Truncated) => {
+                unreachable!("`recv` should never fail with `RecvError::Truncated`")
+            }
+        }
     }
 
-    fn try_recv(
-        &self,
+    fn try_recv_icmp(
+        icmp_socket: &IcmpSocket,
         writer: &mut dyn MultiWrite,
-        _flags: SendRecvFlags,
-    ) -> Result<(usize, Self::Endpoint)> {
-        let result = self.bound_socket.recv(|packet, udp_metadata| {
-            let copied_res = writer.write(&mut VmReader::from(packet));
-            let endpoint = udp_metadata.endpoint;
-            (copied_res, endpoint)
+    ) -> Result<(usize, IpEndpoint)> {
+        let result = icmp_socket.recv(|message, addr| {
+            let copied_res = writer.write(&mut VmReader::from(message));
+            (copied_res, IpEndpoint::new(addr, 0))
         });
 
         match result {
             Ok((Ok(res), endpoint)) => Ok((res, endpoint)),
             Ok((Err(e), _)) => Err(e),
-            Err(RecvError::Exhausted) => {
+            Err(icmp::RecvError::Exhausted) => {
                 return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty")
             }
-            Err(RecvError::Truncated) => {
-                unreachable!("`recv` should never fail with `RecvError::Truncated`")
-            }
         }
     }
 
-    fn try_send(
+    /// Sends a packet and requests the transmit timestamp if `timestamp_key` is not `None`.
+    ///
+    /// For ping sockets, the packet must be an ICMP echo request (including the ICMP header),
+    /// the port of the remote endpoint is ignored, and no timestamp is available.
+    pub(super) fn try_send_with_timestamp(
         &self,
         reader: &mut dyn MultiRead,
-        remote: &Self::Endpoint,
-        _flags: SendRecvFlags,
+        remote: &IpEndpoint,
+        timestamp_key: Option<u32>,
     ) -> Result<usize> {
-        let result = self
-            .bound_socket
-            .send(reader.sum_lens(), *remote, |socket_buffer| {
+        let udp_socket = match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket,
+            BoundSocket::Icmp(icmp_socket) => {
+                return Self::try_send_icmp(icmp_socket, reader, remote)
+            }
+        };
+
+        let result = udp_socket.send_with_timestamp(
+            reader.sum_lens(),
+            *remote,
+            timestamp_key,
+            |socket_buffer| {
                 // FIXME: If copy failed, we should not send any packet.
                 // But current smoltcp API seems not to support this behavior.
                 reader
@@ -87,24 +129,128 @@ impl datagram_common::Bound for BoundDatagram {
                     .inspect_err(|e| {
                         warn!("unexpected UDP packet {e:#?} will be sent");
                     })
-            });
+            },
+        );
 
         match result {
             Ok(inner) => inner,
-            Err(SendError::TooLarge) => {
+            Err(udp::SendError::TooLarge) => {
+                return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+            }
+            Err(udp::SendError::Unaddressable) => {
+                return_errno_with_message!(Errno::EINVAL, "the destination address is invalid");
+            }
+            Err(udp::SendError::BufferFull) => {
+                return_errno_with_message!(Errno::EAGAIN, "the send buffer is full");
+            }
+        }
+    }
+
+    fn try_send_icmp(
+        icmp_socket: &IcmpSocket,
+        reader: &mut dyn MultiRead,
+        remote: &IpEndpoint,
+    ) -> Result<usize> {
+        let len = reader.sum_lens();
+        if len > ICMP_SEND_BUF_LEN {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        let mut message = vec![0; len];
+        reader.read(&mut VmWriter::from(message.as_mut_slice()))?;
+
+        match icmp_socket.send(&remote.addr, &message) {
+            Ok(()) => Ok(len),
+            Err(icmp::SendError::InvalidMessage) => {
+                return_errno_with_message!(Errno::EINVAL, "the message is not an echo request");
+            }
+            Err(icmp::SendError::TooLarge) => {
                 return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
             }
-            Err(SendError::Unaddressable) => {
+            Err(icmp::SendError::Unaddressable) => {
                 return_errno_with_message!(Errno::EINVAL, "the destination address is invalid");
             }
-            Err(SendError::BufferFull) => {
+            Err(icmp::SendError::BufferFull) => {
                 return_errno_with_message!(Errno::EAGAIN, "the send buffer is full");
             }
         }
     }
 
+    /// Moves the transmit timestamps of the sent packets to the error queue.
+    pub(super) fn take_tx_timestamps(&self, error_queue: &mut ErrorQueue) {
+        let BoundSocket::Udp(udp_socket) = &self.bound_socket else {
+            return;
+        };
+
+        while let Some((key, time)) = udp_socket.take_tx_timestamp() {
+            error_queue.push_timestamp(key, TimestampType::Snd, time);
+        }
+    }
+
+    /// Takes an ICMP error message that reports an undeliverable packet.
+    pub(super) fn take_icmp_error(&self) -> Option<IcmpError> {
+        match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket.take_icmp_error(),
+            BoundSocket::Icmp(icmp_socket) => icmp_socket.take_icmp_error(),
+        }
+    }
+}
+
+impl datagram_common::Bound for BoundDatagram {
+    type Endpoint = IpEndpoint;
+
+    fn local_endpoint(&self) -> Self::Endpoint {
+        match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket.local_endpoint(),
+            BoundSocket::Icmp(icmp_socket) => icmp_socket.local_endpoint(),
+        }
+    }
+
+    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
+        self.remote_endpoint.as_ref()
+    }
+
+    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
+        self.remote_endpoint = Some(*endpoint)
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        _flags: SendRecvFlags,
+    ) -> Result<(usize, Self::Endpoint)> {
+        self.try_recv_with_timestamp(writer)
+            .map(|(recv_bytes, endpoint, _)| (recv_bytes, endpoint))
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: &Self::Endpoint,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
+        self.try_send_with_timestamp(reader, remote, None)
+    }
+
     fn check_io_events(&self) -> IoEvents {
-        self.bound_socket.raw_with(|socket| {
+        let udp_socket = match &self.bound_socket {
+            BoundSocket::Udp(udp_socket) => udp_socket,
+            BoundSocket::Icmp(icmp_socket) => {
+                let mut events = IoEvents::empty();
+
+                if icmp_socket.can_recv() {
+                    events |= IoEvents::IN;
+                }
+
+                if icmp_socket.can_send() {
+                    events |= IoEvents::OUT;
+                }
+
+                return events;
+            }
+        };
+
+        let mut events = udp_socket.raw_with(|socket| {
             let mut events = IoEvents::empty();
 
             if socket.can_recv() {
@@ -116,6 +262,12 @@ impl datagram_common::Bound for BoundDatagram {
             }
 
             events
-        })
+        });
+
+        if udp_socket.has_tx_timestamps() {
+            events |= IoEvents::ERR;
+        }
+
+        events
     }
 }
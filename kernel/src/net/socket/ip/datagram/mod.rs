@@ -1,12 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
-use aster_bigtcp::wire::IpEndpoint;
+use aster_bigtcp::{socket::IcmpError, wire::IpEndpoint};
+use aster_rights::ReadOp;
 use unbound::BindOptions;
 
 use self::{bound::BoundDatagram, unbound::UnboundDatagram};
-use super::UNSPECIFIED_LOCAL_ENDPOINT;
+use super::{
+    common::icmp_error_to_errno,
+    local_endpoint_from,
+    options::{IpOptionSet, Ipv6OptionSet, SetIpLevelOption},
+    remote_endpoint_from, socket_addr_from, unspecified_local_endpoint,
+};
 use crate::{
     events::IoEvents,
     match_sock_option_mut,
@@ -14,53 +23,99 @@ use crate::{
         options::{Error as SocketError, SocketOption},
         private::SocketPrivate,
         util::{
-            datagram_common::{select_remote_and_bind, Bound, Inner},
+            datagram_common::{select_remote_and_bind, Inner},
+            error_queue::ErrorQueue,
             options::{SetSocketLevelOption, SocketOptionSet},
             send_recv_flags::SendRecvFlags,
             socket_addr::SocketAddr,
-            MessageHeader,
+            timestamp::{timestamp_control_messages, TimestampControlMessage, TimestampingFlags},
+            ControlMessage, MessageHeader,
         },
         Socket,
     },
     prelude::*,
-    process::signal::{PollHandle, Pollable, Pollee},
+    process::{
+        signal::{PollHandle, Pollable, Pollee},
+        Credentials,
+    },
     util::{MultiRead, MultiWrite},
 };
 
 mod bound;
 mod observer;
+mod ping_group;
 mod unbound;
 
 pub(in crate::net) use self::observer::DatagramObserver;
+pub use self::ping_group::{ping_group_range, set_ping_group_range};
 
 #[derive(Debug, Clone)]
 struct OptionSet {
     socket: SocketOptionSet,
+    ip: IpOptionSet,
+    ipv6: Option<Ipv6OptionSet>,
     // TODO: UDP option set
 }
 
 impl OptionSet {
-    fn new() -> Self {
+    fn new(is_ipv6: bool) -> Self {
         let socket = SocketOptionSet::new_udp();
-        OptionSet { socket }
+        let ip = IpOptionSet::new_udp();
+        let ipv6 = is_ipv6.then(Ipv6OptionSet::new);
+        OptionSet { socket, ip, ipv6 }
+    }
+
+    /// Returns whether the ICMP error message should be queued in the error queue.
+    ///
+    /// This is controlled by `IP_RECVERR` for ICMPv4 messages and by `IPV6_RECVERR` for ICMPv6
+    /// messages.
+    fn recverr(&self, error: &IcmpError) -> bool {
+        if error.is_ipv6 {
+            self.ipv6.as_ref().is_some_and(|ipv6| ipv6.recverr())
+        } else {
+            self.ip.recverr()
+        }
     }
 }
 
 pub struct DatagramSocket {
-    // Lock order: `inner` first, `options` second
+    // Lock order: `inner` first, `options` second, `error_queue` last
     inner: RwMutex<Inner<UnboundDatagram, BoundDatagram>>,
     options: RwLock<OptionSet>,
+    error_queue: Mutex<ErrorQueue>,
 
     is_nonblocking: AtomicBool,
     pollee: Pollee,
 }
 
 impl DatagramSocket {
-    pub fn new(is_nonblocking: bool) -> Arc<Self> {
-        let unbound_datagram = UnboundDatagram::new();
+    /// Creates a UDP socket.
+    pub fn new(is_nonblocking: bool, is_ipv6: bool) -> Arc<Self> {
+        Self::new_with_protocol(is_nonblocking, is_ipv6, false)
+    }
+
+    /// Creates a ping socket, which sends ICMP echo requests and receives ICMP echo replies.
+    ///
+    /// This method fails with `EACCES` if the groups of `credentials` are not allowed to create
+    /// ping sockets (see [`ping_group_range`]).
+    ///
+    /// Reference: <https://lwn.net/Articles/422330/>.
+    pub fn new_ping(
+        is_nonblocking: bool,
+        is_ipv6: bool,
+        credentials: &Credentials<ReadOp>,
+    ) -> Result<Arc<Self>> {
+        ping_group::check_ping_permission(credentials)?;
+
+        Ok(Self::new_with_protocol(is_nonblocking, is_ipv6, true))
+    }
+
+    fn new_with_protocol(is_nonblocking: bool, is_ipv6: bool, is_ping: bool) -> Arc<Self> {
+        let unbound_datagram = UnboundDatagram::new(is_ping);
         Arc::new(Self {
             inner: RwMutex::new(Inner::Unbound(unbound_datagram)),
-            options: RwLock::new(OptionSet::new()),
+            options: RwLock::new(OptionSet::new(is_ipv6)),
+            error_queue: Mutex::new(ErrorQueue::default()),
             is_nonblocking: AtomicBool::new(is_nonblocking),
             pollee: Pollee::new(),
         })
@@ -69,25 +124,53 @@ impl DatagramSocket {
     fn try_recv(
         &self,
         writer: &mut dyn MultiWrite,
-        flags: SendRecvFlags,
-    ) -> Result<(usize, SocketAddr)> {
-        let recv_bytes = self
-            .inner
-            .read()
-            .try_recv(writer, flags)
-            .map(|(recv_bytes, remote_endpoint)| (recv_bytes, remote_endpoint.into()))?;
+    ) -> Result<(usize, SocketAddr, Option<Duration>)> {
+        let (recv_bytes, remote_endpoint, timestamp) = match &*self.inner.read() {
+            Inner::Unbound(_) => {
+                return_errno_with_message!(Errno::EAGAIN, "the socket is not bound")
+            }
+            Inner::Bound(bound_datagram) => {
+                // Like Linux, the socket error is reported before any received packets.
+                if let Some(error) = self.take_socket_error(bound_datagram) {
+                    self.pollee.invalidate();
+                    return Err(error);
+                }
+                bound_datagram.try_recv_with_timestamp(writer)?
+            }
+        };
+        self.pollee.invalidate();
+
+        let remote_addr = socket_addr_from(remote_endpoint, self.options.read().ipv6.as_ref());
+        Ok((recv_bytes, remote_addr, timestamp))
+    }
+
+    fn recv_error(&self, writer: &mut dyn MultiWrite) -> Result<(usize, MessageHeader)> {
+        let inner = self.inner.read();
+        let options = self.options.read();
+        let mut error_queue = self.error_queue.lock();
+
+        if let Inner::Bound(bound_datagram) = &*inner {
+            take_icmp_errors(bound_datagram, &options, &mut error_queue);
+            bound_datagram.take_tx_timestamps(&mut error_queue);
+        }
+        let result = error_queue.recv(writer, &options.socket, options.ipv6.is_some());
+
+        drop(error_queue);
+        drop(options);
+        drop(inner);
         self.pollee.invalidate();
 
-        Ok(recv_bytes)
+        result
     }
 
     fn try_send(
         &self,
         reader: &mut dyn MultiRead,
         remote: Option<&IpEndpoint>,
-        flags: SendRecvFlags,
+        timestamping: TimestampingFlags,
+        is_zerocopy: bool,
     ) -> Result<usize> {
-        let (sent_bytes, iface_to_poll) = select_remote_and_bind(
+        let (sent_bytes, has_errors, iface_to_poll) = select_remote_and_bind(
             &self.inner,
             remote,
             || {
@@ -102,23 +185,115 @@ impl DatagramSocket {
                     .bind_ephemeral(remote_endpoint, &self.pollee)
             },
             |bound_datagram, remote_endpoint| {
-                let sent_bytes = bound_datagram.try_send(reader, remote_endpoint, flags)?;
+                let options = self.options.read();
+                let mut error_queue = self.error_queue.lock();
+
+                // Like Linux, the socket error is reported before sending any packets.
+                take_icmp_errors(bound_datagram, &options, &mut error_queue);
+                if let Some(error) = error_queue.take_socket_error() {
+                    self.pollee.invalidate();
+                    return Err(error);
+                }
+                drop(options);
+
+                let timestamp_key = timestamping
+                    .contains(TimestampingFlags::TX_SOFTWARE)
+                    .then(|| error_queue.timestamp_key(timestamping));
+
+                let sent_bytes = bound_datagram.try_send_with_timestamp(
+                    reader,
+                    remote_endpoint,
+                    timestamp_key,
+                )?;
+                // Like Linux, no zero-copy transmissions are reported for empty messages.
+                let has_errors =
+                    error_queue.on_message_sent(timestamping, is_zerocopy && sent_bytes > 0);
+
                 let iface_to_poll = bound_datagram.iface().clone();
-                Ok((sent_bytes, iface_to_poll))
+                Ok((sent_bytes, has_errors, iface_to_poll))
             },
         )?;
 
-        self.pollee.invalidate();
+        if has_errors {
+            self.pollee.notify(IoEvents::ERR);
+        } else {
+            self.pollee.invalidate();
+        }
         iface_to_poll.poll();
 
         Ok(sent_bytes)
     }
+
+    fn check_io_events(&self) -> IoEvents {
+        let inner = self.inner.read();
+        let mut events = inner.check_io_events();
+
+        let options = self.options.read();
+        let mut error_queue = self.error_queue.lock();
+        if let Inner::Bound(bound_datagram) = &*inner {
+            take_icmp_errors(bound_datagram, &options, &mut error_queue);
+        }
+
+        if !error_queue.is_empty() || error_queue.has_socket_error() {
+            events |= IoEvents::ERR;
+        }
+
+        events
+    }
+
+    /// Takes the socket error after moving the ICMP errors to the error queue.
+    fn take_socket_error(&self, bound_datagram: &BoundDatagram) -> Option<Error> {
+        let options = self.options.read();
+        let mut error_queue = self.error_queue.lock();
+
+        take_icmp_errors(bound_datagram, &options, &mut error_queue);
+        error_queue.take_socket_error()
+    }
+
+    fn test_and_clear_error(&self) -> Option<Error> {
+        let inner = self.inner.read();
+        let error = match &*inner {
+            Inner::Unbound(_) => None,
+            Inner::Bound(bound_datagram) => self.take_socket_error(bound_datagram),
+        };
+        drop(inner);
+
+        self.pollee.invalidate();
+
+        error
+    }
+}
+
+/// Moves the ICMP errors reported by the bound socket to the error queue.
+///
+/// Like Linux, if the error queue is not enabled by `IP_RECVERR` or `IPV6_RECVERR`, the ICMP
+/// errors are discarded, except that hard errors are reported as the socket error if the socket
+/// is connected.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/udp.c#L781>.
+fn take_icmp_errors(
+    bound_datagram: &BoundDatagram,
+    options: &OptionSet,
+    error_queue: &mut ErrorQueue,
+) {
+    while let Some(error) = bound_datagram.take_icmp_error() {
+        let errno = icmp_error_to_errno(&error);
+
+        if options.recverr(&error) {
+            let ipv6 = options.ipv6.as_ref();
+            let offender = socket_addr_from(IpEndpoint::new(error.offender, 0), ipv6);
+            let remote = socket_addr_from(error.remote_endpoint, ipv6);
+            error_queue.push_icmp_error(errno, error, offender, remote);
+        } else if error.is_hard() && bound_datagram.remote_endpoint().is_some() {
+            error_queue.set_socket_error(errno);
+        }
+    }
 }
 
 impl Pollable for DatagramSocket {
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
         self.pollee
-            .poll_with(mask, poller, || self.inner.read().check_io_events())
+            .poll_with(mask, poller, || self.check_io_events())
     }
 }
 
@@ -134,8 +309,10 @@ impl SocketPrivate for DatagramSocket {
 
 impl Socket for DatagramSocket {
     fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
-        let can_reuse = self.options.read().socket.reuse_addr();
+        let options = self.options.read();
+        let endpoint = local_endpoint_from(socket_addr, options.ipv6.as_ref())?;
+        let can_reuse = options.socket.reuse_addr();
+        drop(options);
 
         self.inner
             .write()
@@ -143,19 +320,20 @@ impl Socket for DatagramSocket {
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
+        let endpoint = remote_endpoint_from(socket_addr, self.options.read().ipv6.as_ref())?;
 
         self.inner.write().connect(&endpoint, &self.pollee)
     }
 
     fn addr(&self) -> Result<SocketAddr> {
+        let ipv6 = self.options.read().ipv6;
         let endpoint = self
             .inner
             .read()
             .addr()
-            .unwrap_or(UNSPECIFIED_LOCAL_ENDPOINT);
+            .unwrap_or_else(|| unspecified_local_endpoint(ipv6.as_ref()));
 
-        Ok(endpoint.into())
+        Ok(socket_addr_from(endpoint, ipv6.as_ref()))
     }
 
     fn peer_addr(&self) -> Result<SocketAddr> {
@@ -164,7 +342,10 @@ impl Socket for DatagramSocket {
                 Error::with_message(Errno::ENOTCONN, "the socket is not connected")
             })?;
 
-        Ok(endpoint.into())
+        Ok(socket_addr_from(
+            endpoint,
+            self.options.read().ipv6.as_ref(),
+        ))
     }
 
     fn sendmsg(
@@ -180,21 +361,36 @@ impl Socket for DatagramSocket {
 
         let MessageHeader {
             addr,
-            control_message,
+            control_messages,
+            ..
         } = message_header;
 
+        let options = self.options.read();
+
         let endpoint = match addr {
-            Some(addr) => Some(addr.try_into()?),
+            Some(addr) => Some(remote_endpoint_from(addr, options.ipv6.as_ref())?),
             None => None,
         };
 
-        if control_message.is_some() {
-            // TODO: Support sending control message
-            warn!("sending control message is not supported");
+        let mut timestamping = options.socket.timestamping();
+        for control_message in control_messages {
+            match control_message {
+                ControlMessage::Timestamp(TimestampControlMessage::TxFlags(tx_flags)) => {
+                    timestamping.remove(TimestampingFlags::TX_RECORD_MASK);
+                    timestamping.insert(tx_flags);
+                }
+                // TODO: Support sending other control messages
+                _ => warn!("sending control message is not supported"),
+            }
         }
 
+        // Like Linux, `MSG_ZEROCOPY` is ignored if `SO_ZEROCOPY` is not enabled.
+        let is_zerocopy = flags.contains(SendRecvFlags::MSG_ZEROCOPY) && options.socket.zerocopy();
+
+        drop(options);
+
         // TODO: Block if the send buffer is full
-        self.try_send(reader, endpoint.as_ref(), flags)
+        self.try_send(reader, endpoint.as_ref(), timestamping, is_zerocopy)
     }
 
     fn recvmsg(
@@ -207,12 +403,23 @@ impl Socket for DatagramSocket {
             warn!("unsupported flags: {:?}", flags);
         }
 
-        let (received_bytes, peer_addr) =
-            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+        // Like Linux, receiving from the error queue never blocks.
+        if flags.contains(SendRecvFlags::MSG_ERRQUEUE) {
+            return self.recv_error(writer);
+        }
 
-        // TODO: Receive control message
+        let (received_bytes, peer_addr, timestamp) =
+            self.block_on_msg(flags, IoEvents::IN, || self.try_recv(writer))?;
 
-        let message_header = MessageHeader::new(Some(peer_addr), None);
+        // TODO: Receive other control messages
+        let control_messages = match timestamp {
+            Some(timestamp) => {
+                timestamp_control_messages(&self.options.read().socket, timestamp, false)
+            }
+            None => Vec::new(),
+        };
+
+        let message_header = MessageHeader::new(Some(peer_addr), control_messages);
 
         Ok((received_bytes, message_header))
     }
@@ -220,21 +427,58 @@ impl Socket for DatagramSocket {
     fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
         match_sock_option_mut!(option, {
             socket_errors: SocketError => {
-                // TODO: Support socket errors for UDP sockets
-                socket_errors.set(None);
+                socket_errors.set(self.test_and_clear_error());
                 return Ok(());
             },
             _ => ()
         });
 
-        self.options.read().socket.get_option(option)
+        let options = self.options.read();
+
+        // Deal with socket-level options
+        match options.socket.get_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IP-level options
+        match options.ip.get_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IPv6-level options
+        match options.ipv6.as_ref() {
+            Some(ipv6) => ipv6.get_option(option),
+            None => {
+                return_errno_with_message!(
+                    Errno::ENOPROTOOPT,
+                    "the socket option to get is unknown"
+                )
+            }
+        }
     }
 
     fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
         let inner = self.inner.read();
         let mut options = self.options.write();
 
-        match options.socket.set_option(option, &*inner) {
+        let result = match options.socket.set_option(option, self) {
+            // Deal with IP-level options
+            Err(err) if err.error() == Errno::ENOPROTOOPT => {
+                match options.ip.set_option(option, self) {
+                    // Deal with IPv6-level options
+                    Err(err) if err.error() == Errno::ENOPROTOOPT => match options.ipv6.as_mut() {
+                        Some(ipv6) => ipv6.set_option(option),
+                        None => Err(err),
+                    },
+                    result => result,
+                }
+            }
+            result => result,
+        };
+
+        match result {
             Err(e) => Err(e),
             Ok(need_iface_poll) => {
                 let iface_to_poll = need_iface_poll
@@ -257,4 +501,17 @@ impl Socket for DatagramSocket {
     }
 }
 
-impl SetSocketLevelOption for Inner<UnboundDatagram, BoundDatagram> {}
+impl SetSocketLevelOption for DatagramSocket {
+    fn reset_timestamp_key(&self) {
+        self.error_queue.lock().reset_timestamp_key();
+    }
+}
+
+impl SetIpLevelOption for DatagramSocket {
+    fn set_hdrincl(&self, _hdrincl: bool) -> Result<()> {
+        return_errno_with_message!(
+            Errno::ENOPROTOOPT,
+            "IP_HDRINCL cannot be set on UDP sockets"
+        );
+    }
+}
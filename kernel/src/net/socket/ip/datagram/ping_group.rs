@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The groups that are allowed to create ping sockets.
+//!
+//! The range of the groups is configured via `/proc/sys/net/ipv4/ping_group_range`, which applies
+//! to both IPv4 and IPv6 ping sockets.
+
+use aster_rights::ReadOp;
+
+use crate::{
+    prelude::*,
+    process::{Credentials, Gid},
+};
+
+/// The range of the groups that are allowed to create ping sockets (inclusive).
+///
+/// Like Linux, the default range is empty, so no one (not even root) can create ping sockets
+/// until the range is configured.
+static PING_GROUP_RANGE: RwLock<(Gid, Gid)> = RwLock::new((Gid::new(1), Gid::new(0)));
+
+/// The maximum group ID that can be configured.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/ipv4/sysctl_net_ipv4.c#L37>.
+const MAX_GID: u32 = i32::MAX as u32;
+
+/// Returns the range of the groups that are allowed to create ping sockets.
+pub fn ping_group_range() -> (Gid, Gid) {
+    *PING_GROUP_RANGE.read()
+}
+
+/// Sets the range of the groups that are allowed to create ping sockets.
+///
+/// Like Linux, if `lo` is greater than `hi`, the range is reset to the default empty range.
+pub fn set_ping_group_range(lo: u32, hi: u32) -> Result<()> {
+    if lo > MAX_GID || hi > MAX_GID {
+        return_errno_with_message!(Errno::EINVAL, "the group ID is out of range");
+    }
+
+    let range = if lo <= hi {
+        (Gid::new(lo), Gid::new(hi))
+    } else {
+        (Gid::new(1), Gid::new(0))
+    };
+    *PING_GROUP_RANGE.write() = range;
+
+    Ok(())
+}
+
+/// Checks whether a ping socket can be created with the credentials.
+///
+/// Either the effective group or one of the supplementary groups must be in the range.
+pub(super) fn check_ping_permission(credentials: &Credentials<ReadOp>) -> Result<()> {
+    let (lo, hi) = ping_group_range();
+    let is_allowed = |gid: &Gid| lo <= *gid && *gid <= hi;
+
+    if is_allowed(&credentials.egid()) || credentials.groups().iter().any(is_allowed) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EACCES,
+        "the group is not allowed to create ping sockets"
+    );
+}
@@ -1,8 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::{socket::UdpSocket, wire::IpEndpoint};
+use aster_bigtcp::{
+    socket::{IcmpSocket, UdpSocket},
+    wire::IpEndpoint,
+};
 
-use super::{bound::BoundDatagram, DatagramObserver};
+use super::{
+    bound::{BoundDatagram, BoundSocket},
+    DatagramObserver,
+};
 use crate::{
     events::IoEvents,
     net::socket::{
@@ -14,12 +20,13 @@ use crate::{
 };
 
 pub(super) struct UnboundDatagram {
-    _private: (),
+    /// Whether the socket is a ping socket (i.e., an ICMP socket), or a UDP socket otherwise.
+    is_ping: bool,
 }
 
 impl UnboundDatagram {
-    pub(super) fn new() -> Self {
-        Self { _private: () }
+    pub(super) fn new(is_ping: bool) -> Self {
+        Self { is_ping }
     }
 }
 
@@ -40,16 +47,22 @@ impl datagram_common::Unbound for UnboundDatagram {
         options: BindOptions,
     ) -> Result<Self::Bound> {
         let bound_port = bind_port(endpoint, options.can_reuse)?;
+        let observer = DatagramObserver::new(pollee.clone());
+
+        // For ping sockets, the port number is used as the identifier of echo requests.
+        if self.is_ping {
+            let bound_socket = IcmpSocket::new_bind(bound_port, observer);
+            return Ok(BoundDatagram::new(BoundSocket::Icmp(bound_socket)));
+        }
 
-        let bound_socket =
-            match UdpSocket::new_bind(bound_port, DatagramObserver::new(pollee.clone())) {
-                Ok(bound_socket) => bound_socket,
-                Err((_, err)) => {
-                    unreachable!("`new_bind` fails with {:?}, which should not happen", err)
-                }
-            };
+        let bound_socket = match UdpSocket::new_bind(bound_port, observer) {
+            Ok(bound_socket) => bound_socket,
+            Err((_, err)) => {
+                unreachable!("`new_bind` fails with {:?}, which should not happen", err)
+            }
+        };
 
-        Ok(BoundDatagram::new(bound_socket))
+        Ok(BoundDatagram::new(BoundSocket::Udp(bound_socket)))
     }
 
     fn bind_ephemeral(
@@ -57,7 +70,7 @@ impl datagram_common::Unbound for UnboundDatagram {
         remote_endpoint: &Self::Endpoint,
         pollee: &Pollee,
     ) -> Result<Self::Bound> {
-        let endpoint = get_ephemeral_endpoint(remote_endpoint);
+        let endpoint = get_ephemeral_endpoint(remote_endpoint)?;
         self.bind(&endpoint, pollee, BindOptions { can_reuse: false })
     }
 
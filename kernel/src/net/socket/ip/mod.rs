@@ -6,4 +6,6 @@ pub mod datagram;
 pub mod options;
 pub mod stream;
 
-use addr::UNSPECIFIED_LOCAL_ENDPOINT;
+use addr::{
+    local_endpoint_from, remote_endpoint_from, socket_addr_from, unspecified_local_endpoint,
+};
@@ -17,6 +17,7 @@ pub(super) struct IpOptionSet {
     tos: u8,
     ttl: IpTtl,
     hdrincl: bool,
+    recverr: bool,
 }
 
 const DEFAULT_TTL: u8 = 64;
@@ -28,6 +29,16 @@ impl IpOptionSet {
             tos: 0,
             ttl: IpTtl(None),
             hdrincl: false,
+            recverr: false,
+        }
+    }
+
+    pub(super) const fn new_udp() -> Self {
+        Self {
+            tos: 0,
+            ttl: IpTtl(None),
+            hdrincl: false,
+            recverr: false,
         }
     }
 
@@ -45,6 +56,10 @@ impl IpOptionSet {
                 let hdrincl = self.hdrincl();
                 ip_hdrincl.set(hdrincl);
             },
+            ip_recverr: RecvErr => {
+                let recverr = self.recverr();
+                ip_recverr.set(recverr);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option is unknown")
         });
 
@@ -73,6 +88,10 @@ impl IpOptionSet {
                 socket.set_hdrincl(*hdrincl)?;
                 self.set_hdrincl(*hdrincl);
             },
+            ip_recverr: RecvErr => {
+                let recverr = ip_recverr.get().unwrap();
+                self.set_recverr(*recverr);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
         });
 
@@ -84,6 +103,64 @@ impl_socket_options!(
     pub struct Tos(i32);
     pub struct Ttl(IpTtl);
     pub struct Hdrincl(bool);
+    pub struct RecvErr(bool);
+);
+
+/// IPv6-level socket options.
+///
+/// Only sockets in the `AF_INET6` domain have these options.
+#[derive(Debug, Clone, Copy, CopyGetters, Setters)]
+#[get_copy = "pub"]
+#[set = "pub"]
+pub(super) struct Ipv6OptionSet {
+    v6only: bool,
+    recverr: bool,
+}
+
+impl Ipv6OptionSet {
+    pub(super) const fn new() -> Self {
+        Self {
+            v6only: false,
+            recverr: false,
+        }
+    }
+
+    pub(super) fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            ipv6_v6only: V6Only => {
+                let v6only = self.v6only();
+                ipv6_v6only.set(v6only);
+            },
+            ipv6_recverr: Ipv6RecvErr => {
+                let recverr = self.recverr();
+                ipv6_recverr.set(recverr);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option is unknown")
+        });
+
+        Ok(())
+    }
+
+    pub(super) fn set_option(&mut self, option: &dyn SocketOption) -> Result<NeedIfacePoll> {
+        match_sock_option_ref!(option, {
+            ipv6_v6only: V6Only => {
+                let v6only = ipv6_v6only.get().unwrap();
+                self.set_v6only(*v6only);
+            },
+            ipv6_recverr: Ipv6RecvErr => {
+                let recverr = ipv6_recverr.get().unwrap();
+                self.set_recverr(*recverr);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
+        });
+
+        Ok(NeedIfacePoll::FALSE)
+    }
+}
+
+impl_socket_options!(
+    pub struct V6Only(bool);
+    pub struct Ipv6RecvErr(bool);
 );
 
 #[derive(Debug, Clone, Copy)]
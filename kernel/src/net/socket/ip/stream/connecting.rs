@@ -9,7 +9,10 @@ use aster_bigtcp::{
 use super::{connected::ConnectedStream, init::InitStream, StreamObserver};
 use crate::{
     events::IoEvents,
-    net::iface::{BoundPort, Iface, TcpConnection},
+    net::{
+        iface::{BoundPort, Iface, TcpConnection},
+        socket::ip::common::icmp_error_to_errno,
+    },
     prelude::*,
 };
 
@@ -82,14 +85,23 @@ impl ConnectingStream {
                 self.remote_endpoint,
                 true,
             )),
-            ConnectState::Refused => ConnResult::Refused(InitStream::new_refused(
-                self.tcp_conn.into_bound_port().unwrap(),
-            )),
+            ConnectState::Refused => {
+                // If the connection is refused by an ICMP error message, the error code is
+                // determined by the message. Otherwise, it is refused by a RST segment.
+                let errno = self
+                    .tcp_conn
+                    .icmp_error()
+                    .map_or(Errno::ECONNREFUSED, |error| icmp_error_to_errno(&error));
+                ConnResult::Refused(InitStream::new_refused(
+                    self.tcp_conn.into_bound_port().unwrap(),
+                    errno,
+                ))
+            }
         }
     }
 
     pub fn local_endpoint(&self) -> IpEndpoint {
-        self.tcp_conn.local_endpoint().unwrap()
+        self.tcp_conn.local_endpoint()
     }
 
     pub fn remote_endpoint(&self) -> IpEndpoint {
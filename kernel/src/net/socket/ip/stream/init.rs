@@ -2,7 +2,7 @@
 
 use core::{
     net::Ipv4Addr,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{AtomicI32, Ordering},
 };
 
 use aster_bigtcp::{socket::RawTcpOption, wire::IpEndpoint};
@@ -36,12 +36,12 @@ pub struct InitStream {
     ///    `connect()`, which checks and resets the boolean value and returns an appropriate error
     ///    code.
     is_connect_done: bool,
-    /// Indicates whether the socket error is `ECONNREFUSED`.
+    /// The socket error, or zero if there is no error.
     ///
-    /// This boolean value is set to true when the connection is refused and set to false when the
-    /// error code is reported via `getsockopt(SOL_SOCKET, SO_ERROR)`, `send()`, `recv()`, or
-    /// `connect()`.
-    is_conn_refused: AtomicBool,
+    /// The error code is set when the connection is refused (e.g., `ECONNREFUSED`, or
+    /// `EHOSTUNREACH` if an ICMP error message is received) and is cleared when the error code is
+    /// reported via `getsockopt(SOL_SOCKET, SO_ERROR)`, `send()`, `recv()`, or `connect()`.
+    conn_error: AtomicI32,
 }
 
 impl InitStream {
@@ -49,7 +49,7 @@ impl InitStream {
         Self {
             bound_port: None,
             is_connect_done: true,
-            is_conn_refused: AtomicBool::new(false),
+            conn_error: AtomicI32::new(0),
         }
     }
 
@@ -57,15 +57,15 @@ impl InitStream {
         Self {
             bound_port: Some(bound_port),
             is_connect_done: true,
-            is_conn_refused: AtomicBool::new(false),
+            conn_error: AtomicI32::new(0),
         }
     }
 
-    pub fn new_refused(bound_port: BoundPort) -> Self {
+    pub fn new_refused(bound_port: BoundPort, errno: Errno) -> Self {
         Self {
             bound_port: Some(bound_port),
             is_connect_done: false,
-            is_conn_refused: AtomicBool::new(true),
+            conn_error: AtomicI32::new(errno as i32),
         }
     }
 
@@ -93,8 +93,9 @@ impl InitStream {
         let bound_port = if let Some(bound_port) = self.bound_port {
             bound_port
         } else {
-            let endpoint = get_ephemeral_endpoint(remote_endpoint);
-            match bind_port(&endpoint, false) {
+            let result = get_ephemeral_endpoint(remote_endpoint)
+                .and_then(|endpoint| bind_port(&endpoint, false));
+            match result {
                 Ok(bound_port) => bound_port,
                 Err(err) => return Err((err, self)),
             }
@@ -103,7 +104,10 @@ impl InitStream {
         ConnectingStream::new(bound_port, *remote_endpoint, option, observer).map_err(
             |(err, bound_port)| {
                 if err.error() == Errno::ECONNREFUSED {
-                    (err, InitStream::new_refused(bound_port))
+                    (
+                        err,
+                        InitStream::new_refused(bound_port, Errno::ECONNREFUSED),
+                    )
                 } else {
                     (err, InitStream::new_bound(bound_port))
                 }
@@ -118,10 +122,9 @@ impl InitStream {
 
         self.is_connect_done = true;
 
-        let is_conn_refused = self.is_conn_refused.get_mut();
-        if *is_conn_refused {
-            *is_conn_refused = false;
-            return_errno_with_message!(Errno::ECONNREFUSED, "the connection is refused");
+        let conn_error = core::mem::take(self.conn_error.get_mut());
+        if let Ok(errno) = Errno::try_from(conn_error) {
+            return_errno_with_message!(errno, "the connection is refused");
         } else {
             return_errno_with_message!(
                 Errno::ECONNABORTED,
@@ -133,6 +136,7 @@ impl InitStream {
     pub fn listen(
         self,
         backlog: usize,
+        reuse_port: bool,
         option: &RawTcpOption,
         observer: StreamObserver,
     ) -> core::result::Result<ListenStream, (Error, Self)> {
@@ -158,7 +162,7 @@ impl InitStream {
             ));
         };
 
-        match ListenStream::new(bound_port, backlog, option, observer) {
+        match ListenStream::new(bound_port, backlog, reuse_port, option, observer) {
             Ok(listen_stream) => Ok(listen_stream),
             Err((bound_port, error)) => Err((error, Self::new_bound(bound_port))),
         }
@@ -192,16 +196,14 @@ impl InitStream {
     }
 
     pub fn local_endpoint(&self) -> Option<IpEndpoint> {
-        self.bound_port
-            .as_ref()
-            .map(|bound_port| bound_port.endpoint().unwrap())
+        self.bound_port.as_ref().map(BoundPort::endpoint)
     }
 
     pub(super) fn check_io_events(&self) -> IoEvents {
         // Linux adds OUT and HUP events for a newly created socket
         let mut events = IoEvents::OUT | IoEvents::HUP;
 
-        if self.is_conn_refused.load(Ordering::Relaxed) {
+        if self.conn_error.load(Ordering::Relaxed) != 0 {
             events |= IoEvents::ERR;
         }
 
@@ -209,13 +211,9 @@ impl InitStream {
     }
 
     pub(super) fn test_and_clear_error(&self) -> Option<Error> {
-        if self.is_conn_refused.swap(false, Ordering::Relaxed) {
-            Some(Error::with_message(
-                Errno::ECONNREFUSED,
-                "the connection is refused",
-            ))
-        } else {
-            None
-        }
+        let conn_error = self.conn_error.swap(0, Ordering::Relaxed);
+        Errno::try_from(conn_error)
+            .ok()
+            .map(|errno| Error::with_message(errno, "the connection is refused"))
     }
 }
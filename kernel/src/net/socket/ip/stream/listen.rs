@@ -21,13 +21,11 @@ impl ListenStream {
     pub fn new(
         bound_port: BoundPort,
         backlog: usize,
+        reuse_port: bool,
         option: &RawTcpOption,
         observer: StreamObserver,
     ) -> core::result::Result<Self, (BoundPort, Error)> {
-        const SOMAXCONN: usize = 4096;
-        let max_conn = SOMAXCONN.min(backlog);
-
-        match TcpListener::new_listen(bound_port, max_conn, option, observer) {
+        match TcpListener::new_listen(bound_port, max_conn(backlog), reuse_port, option, observer) {
             Ok(tcp_listener) => Ok(Self { tcp_listener }),
             Err((bound_port, ListenError::AddressInUse)) => Err((
                 bound_port,
@@ -39,6 +37,11 @@ impl ListenStream {
         }
     }
 
+    /// Updates the backlog after `listen()` is called again.
+    pub fn set_backlog(&self, backlog: usize) {
+        self.tcp_listener.set_max_conn(max_conn(backlog));
+    }
+
     pub fn try_accept(&self) -> Result<ConnectedStream> {
         let (new_conn, remote_endpoint) = self.tcp_listener.accept().ok_or_else(|| {
             Error::with_message(Errno::EAGAIN, "no pending connection is available")
@@ -48,7 +51,7 @@ impl ListenStream {
     }
 
     pub fn local_endpoint(&self) -> IpEndpoint {
-        self.tcp_listener.local_endpoint().unwrap()
+        self.tcp_listener.local_endpoint()
     }
 
     pub fn iface(&self) -> &Arc<Iface> {
@@ -78,3 +81,9 @@ impl ListenStream {
         self.tcp_listener
     }
 }
+
+/// Returns the maximum number of connections in each of the SYN queue and the accept queue.
+fn max_conn(backlog: usize) -> usize {
+    const SOMAXCONN: usize = 4096;
+    SOMAXCONN.min(backlog)
+}
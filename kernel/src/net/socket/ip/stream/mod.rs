@@ -3,7 +3,8 @@
 use core::sync::atomic::{AtomicBool, Ordering};
 
 use aster_bigtcp::{
-    socket::{NeedIfacePoll, RawTcpOption, RawTcpSetOption},
+    socket::{KeepAlive, NeedIfacePoll, RawTcpOption, RawTcpSetOption},
+    time::Duration,
     wire::IpEndpoint,
 };
 use connected::{close_and_linger, ConnectedStream};
@@ -11,16 +12,17 @@ use connecting::{ConnResult, ConnectingStream};
 use init::InitStream;
 use listen::ListenStream;
 use options::{
-    Congestion, DeferAccept, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, UserTimeout, WindowClamp,
-    KEEPALIVE_INTERVAL,
+    Congestion, Cork, DeferAccept, Inq, KeepCnt, KeepIdle, KeepIntvl, MaxSegment, NoDelay, SynCnt,
+    UserTimeout, WindowClamp,
 };
 use ostd::sync::{PreemptDisabled, RwLockReadGuard, RwLockWriteGuard};
 use takeable::Takeable;
 use util::{Retrans, TcpOptionSet};
 
 use super::{
-    options::{IpOptionSet, SetIpLevelOption},
-    UNSPECIFIED_LOCAL_ENDPOINT,
+    local_endpoint_from,
+    options::{IpOptionSet, Ipv6OptionSet, SetIpLevelOption},
+    remote_endpoint_from, socket_addr_from, unspecified_local_endpoint,
 };
 use crate::{
     events::IoEvents,
@@ -32,10 +34,12 @@ use crate::{
             options::{Error as SocketError, SocketOption},
             private::SocketPrivate,
             util::{
+                error_queue::ErrorQueue,
                 options::{SetSocketLevelOption, SocketOptionSet},
                 send_recv_flags::SendRecvFlags,
                 shutdown_cmd::SockShutdownCmd,
                 socket_addr::SocketAddr,
+                timestamp::TimestampingFlags,
                 MessageHeader,
             },
             Socket,
@@ -58,9 +62,10 @@ pub(in crate::net) use self::observer::StreamObserver;
 pub use self::util::CongestionControl;
 
 pub struct StreamSocket {
-    // Lock order: `state` first, `options` second
+    // Lock order: `state` first, `options` second, `error_queue` last
     state: RwLock<Takeable<State>, PreemptDisabled>,
     options: RwLock<OptionSet>,
+    error_queue: Mutex<ErrorQueue>,
 
     is_nonblocking: AtomicBool,
     pollee: Pollee,
@@ -81,48 +86,80 @@ enum State {
 struct OptionSet {
     socket: SocketOptionSet,
     ip: IpOptionSet,
+    ipv6: Option<Ipv6OptionSet>,
     tcp: TcpOptionSet,
 }
 
 impl OptionSet {
-    fn new() -> Self {
+    fn new(ipv6: Option<Ipv6OptionSet>) -> Self {
         let socket = SocketOptionSet::new_tcp();
         let ip = IpOptionSet::new_tcp();
         let tcp = TcpOptionSet::new();
-        OptionSet { socket, ip, tcp }
+        OptionSet {
+            socket,
+            ip,
+            ipv6,
+            tcp,
+        }
     }
 
     fn raw(&self) -> RawTcpOption {
+        let defer_accept_secs = self.tcp.defer_accept().to_secs();
+
         RawTcpOption {
-            keep_alive: self.socket.keep_alive().then_some(KEEPALIVE_INTERVAL),
+            keep_alive: self.socket.keep_alive().then(|| self.tcp.keep_alive()),
             is_nagle_enabled: !self.tcp.no_delay(),
+            is_corked: self.tcp.cork(),
+            congestion_control: self.tcp.congestion().to_raw(),
+            defer_accept: (defer_accept_secs != 0)
+                .then(|| Duration::from_secs(defer_accept_secs as u64)),
         }
     }
 }
 
 impl StreamSocket {
-    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+    pub fn new(is_nonblocking: bool, is_ipv6: bool) -> Arc<Self> {
         let init_stream = InitStream::new();
+        let ipv6 = is_ipv6.then(Ipv6OptionSet::new);
         Arc::new(Self {
             state: RwLock::new(Takeable::new(State::Init(init_stream))),
-            options: RwLock::new(OptionSet::new()),
+            options: RwLock::new(OptionSet::new(ipv6)),
+            error_queue: Mutex::new(ErrorQueue::default()),
             is_nonblocking: AtomicBool::new(is_nonblocking),
             pollee: Pollee::new(),
         })
     }
 
-    fn new_accepted(connected_stream: ConnectedStream) -> Arc<Self> {
+    fn new_accepted(connected_stream: ConnectedStream, ipv6: Option<Ipv6OptionSet>) -> Arc<Self> {
         let options = connected_stream.raw_with(|raw_tcp_socket| {
-            let mut options = OptionSet::new();
-
-            if raw_tcp_socket.keep_alive().is_some() {
+            let mut options = OptionSet::new(ipv6);
+
+            if let Some(KeepAlive {
+                idle,
+                interval,
+                probes,
+            }) = raw_tcp_socket.keep_alive_option()
+            {
                 options.socket.set_keep_alive(true);
+                options.tcp.set_keep_idle(idle.secs() as u32);
+                options.tcp.set_keep_intvl(interval.secs() as u32);
+                options.tcp.set_keep_cnt(probes);
             }
 
             if !raw_tcp_socket.nagle_enabled() {
                 options.tcp.set_no_delay(true);
             }
 
+            if raw_tcp_socket.is_corked() {
+                options.tcp.set_cork(true);
+            }
+
+            if let Some(congestion) =
+                CongestionControl::from_raw(raw_tcp_socket.congestion_control())
+            {
+                options.tcp.set_congestion(congestion);
+            }
+
             // TODO: Update other options for a newly-accepted socket
 
             options
@@ -134,6 +171,7 @@ impl StreamSocket {
         Arc::new(Self {
             options: RwLock::new(options),
             state: RwLock::new(Takeable::new(State::Connected(connected_stream))),
+            error_queue: Mutex::new(ErrorQueue::default()),
             is_nonblocking: AtomicBool::new(false),
             pollee,
         })
@@ -298,9 +336,13 @@ impl StreamSocket {
         };
 
         let accepted = listen_stream.try_accept().map(|connected_stream| {
+            let ipv6 = self.options.read().ipv6;
             let remote_endpoint = connected_stream.remote_endpoint();
-            let accepted_socket = Self::new_accepted(connected_stream);
-            (accepted_socket as _, remote_endpoint.into())
+            let accepted_socket = Self::new_accepted(connected_stream, ipv6);
+            (
+                accepted_socket as _,
+                socket_addr_from(remote_endpoint, ipv6.as_ref()),
+            )
         });
         let iface_to_poll = listen_stream.iface().clone();
 
@@ -386,12 +428,32 @@ impl StreamSocket {
     fn check_io_events(&self) -> IoEvents {
         let state = self.read_updated_state();
 
-        match state.as_ref() {
+        let mut events = match state.as_ref() {
             State::Init(init_stream) => init_stream.check_io_events(),
             State::Connecting(connecting_stream) => connecting_stream.check_io_events(),
             State::Listen(listen_stream) => listen_stream.check_io_events(),
             State::Connected(connected_stream) => connected_stream.check_io_events(),
+        };
+
+        drop(state);
+        if !self.error_queue.lock().is_empty() {
+            events |= IoEvents::ERR;
         }
+
+        events
+    }
+
+    fn recv_error(&self, writer: &mut dyn MultiWrite) -> Result<(usize, MessageHeader)> {
+        let options = self.options.read();
+        let result = self
+            .error_queue
+            .lock()
+            .recv(writer, &options.socket, options.ipv6.is_some());
+        drop(options);
+
+        self.pollee.invalidate();
+
+        result
     }
 
     fn test_and_clear_error(&self) -> Option<Error> {
@@ -426,19 +488,20 @@ impl SocketPrivate for StreamSocket {
 
 impl Socket for StreamSocket {
     fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
+        let endpoint = local_endpoint_from(socket_addr, self.options.read().ipv6.as_ref())?;
 
         let mut state = self.write_updated_state();
         let State::Init(init_stream) = state.as_mut() else {
             return_errno_with_message!(Errno::EINVAL, "the socket is already bound to an address");
         };
 
-        let can_reuse = self.options.read().socket.reuse_addr();
+        let options = self.options.read();
+        let can_reuse = options.socket.reuse_addr() || options.socket.reuse_port();
         init_stream.bind(&endpoint, can_reuse)
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
-        let remote_endpoint = socket_addr.try_into()?;
+        let remote_endpoint = remote_endpoint_from(socket_addr, self.options.read().ipv6.as_ref())?;
 
         if let Some(result) = self.start_connect(&remote_endpoint) {
             return result;
@@ -451,12 +514,15 @@ impl Socket for StreamSocket {
         let mut state = self.write_updated_state();
 
         let options = self.options.read();
+        let reuse_port = options.socket.reuse_port();
         let raw_option = options.raw();
 
         state.borrow_result(|owned_state| {
             let init_stream = match owned_state {
                 State::Init(init_stream) => init_stream,
                 State::Listen(listen_stream) => {
+                    // Like Linux, calling `listen()` again updates the backlog.
+                    listen_stream.set_backlog(backlog);
                     return (State::Listen(listen_stream), Ok(()));
                 }
                 State::Connecting(_) | State::Connected(_) => {
@@ -472,6 +538,7 @@ impl Socket for StreamSocket {
 
             let listen_stream = match init_stream.listen(
                 backlog,
+                reuse_port,
                 &raw_option,
                 StreamObserver::new(self.pollee.clone()),
             ) {
@@ -512,15 +579,16 @@ impl Socket for StreamSocket {
 
     fn addr(&self) -> Result<SocketAddr> {
         let state = self.read_updated_state();
+        let ipv6 = self.options.read().ipv6;
         let local_endpoint = match state.as_ref() {
             State::Init(init_stream) => init_stream
                 .local_endpoint()
-                .unwrap_or(UNSPECIFIED_LOCAL_ENDPOINT),
+                .unwrap_or_else(|| unspecified_local_endpoint(ipv6.as_ref())),
             State::Connecting(connecting_stream) => connecting_stream.local_endpoint(),
             State::Listen(listen_stream) => listen_stream.local_endpoint(),
             State::Connected(connected_stream) => connected_stream.local_endpoint(),
         };
-        Ok(local_endpoint.into())
+        Ok(socket_addr_from(local_endpoint, ipv6.as_ref()))
     }
 
     fn peer_addr(&self) -> Result<SocketAddr> {
@@ -532,7 +600,10 @@ impl Socket for StreamSocket {
             State::Connecting(connecting_stream) => connecting_stream.remote_endpoint(),
             State::Connected(connected_stream) => connected_stream.remote_endpoint(),
         };
-        Ok(remote_endpoint.into())
+        Ok(socket_addr_from(
+            remote_endpoint,
+            self.options.read().ipv6.as_ref(),
+        ))
     }
 
     fn sendmsg(
@@ -547,21 +618,35 @@ impl Socket for StreamSocket {
         }
 
         let MessageHeader {
-            control_message, ..
+            control_messages, ..
         } = message_header;
 
         // According to the Linux man pages, `EISCONN` _may_ be returned when the destination
         // address is specified for a connection-mode socket. In practice, the destination address
         // is simply ignored. We follow the same behavior as the Linux implementation to ignore it.
 
-        if control_message.is_some() {
+        if !control_messages.is_empty() {
             // TODO: Support sending control message
             warn!("sending control message is not supported");
         }
 
-        self.block_on(IoEvents::OUT, || self.try_send(reader, flags))
+        // Like Linux, `MSG_ZEROCOPY` is ignored if `SO_ZEROCOPY` is not enabled.
+        let is_zerocopy =
+            flags.contains(SendRecvFlags::MSG_ZEROCOPY) && self.options.read().socket.zerocopy();
 
+        let sent_bytes =
+            self.block_on_msg(flags, IoEvents::OUT, || self.try_send(reader, flags))?;
         // TODO: Trigger `SIGPIPE` if the error code is `EPIPE` and `MSG_NOSIGNAL` is not specified
+
+        // TODO: Support transmit timestamps on TCP sockets
+        if is_zerocopy && sent_bytes > 0 {
+            self.error_queue
+                .lock()
+                .on_message_sent(TimestampingFlags::empty(), true);
+            self.pollee.notify(IoEvents::ERR);
+        }
+
+        Ok(sent_bytes)
     }
 
     fn recvmsg(
@@ -574,13 +659,19 @@ impl Socket for StreamSocket {
             warn!("unsupported flags: {:?}", flags);
         }
 
-        let (received_bytes, _) = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+        // Like Linux, receiving from the error queue never blocks.
+        if flags.contains(SendRecvFlags::MSG_ERRQUEUE) {
+            return self.recv_error(writer);
+        }
+
+        let (received_bytes, _) =
+            self.block_on_msg(flags, IoEvents::IN, || self.try_recv(writer, flags))?;
 
         // TODO: Receive control message
 
         // According to <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/tcp.c#L2645>,
         // peer address is ignored for connected socket.
-        let message_header = MessageHeader::new(None, None);
+        let message_header = MessageHeader::new(None, Vec::new());
 
         Ok((received_bytes, message_header))
     }
@@ -608,6 +699,14 @@ impl Socket for StreamSocket {
             res => return res,
         }
 
+        // Deal with IPv6-level options
+        if let Some(ipv6) = options.ipv6.as_ref() {
+            match ipv6.get_option(option) {
+                Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+                res => return res,
+            }
+        }
+
         // Deal with TCP-level options
         // FIXME: Here we only return the previously set values, without actually
         // asking the underlying sockets for the real, effective values.
@@ -631,10 +730,22 @@ impl Socket for StreamSocket {
                     tcp_maxseg.set(maxseg);
                 }
             },
+            tcp_cork: Cork => {
+                let cork = options.tcp.cork();
+                tcp_cork.set(cork);
+            },
             tcp_keep_idle: KeepIdle => {
                 let keep_idle = options.tcp.keep_idle();
                 tcp_keep_idle.set(keep_idle);
             },
+            tcp_keep_intvl: KeepIntvl => {
+                let keep_intvl = options.tcp.keep_intvl();
+                tcp_keep_intvl.set(keep_intvl);
+            },
+            tcp_keep_cnt: KeepCnt => {
+                let keep_cnt = options.tcp.keep_cnt();
+                tcp_keep_cnt.set(keep_cnt);
+            },
             tcp_syn_cnt: SynCnt => {
                 let syn_cnt = options.tcp.syn_cnt();
                 tcp_syn_cnt.set(syn_cnt);
@@ -671,13 +782,17 @@ impl Socket for StreamSocket {
         let mut options = self.options.write();
 
         // Deal with socket-level options
-        let need_iface_poll = match options.socket.set_option(option, state.as_mut()) {
+        let socket = StateWithTcpOptions {
+            state: state.as_ref(),
+            tcp: options.tcp,
+        };
+        let need_iface_poll = match options.socket.set_option(option, &socket) {
             Err(err) if err.error() == Errno::ENOPROTOOPT => {
                 // Deal with IP-level options
                 match options.ip.set_option(option, state.as_mut()) {
                     Err(err) if err.error() == Errno::ENOPROTOOPT => {
-                        // Deal with TCP-level options
-                        do_tcp_setsockopt(option, &mut options, state.as_mut())?
+                        // Deal with IPv6-level and TCP-level options
+                        do_ipv6_or_tcp_setsockopt(option, &mut options, state.as_mut())?
                     }
                     Err(err) => return Err(err),
                     Ok(need_iface_poll) => need_iface_poll,
@@ -700,6 +815,21 @@ impl Socket for StreamSocket {
     }
 }
 
+fn do_ipv6_or_tcp_setsockopt(
+    option: &dyn SocketOption,
+    options: &mut OptionSet,
+    state: &mut State,
+) -> Result<NeedIfacePoll> {
+    if let Some(ipv6) = options.ipv6.as_mut() {
+        match ipv6.set_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+    }
+
+    do_tcp_setsockopt(option, options, state)
+}
+
 fn do_tcp_setsockopt(
     option: &dyn SocketOption,
     options: &mut OptionSet,
@@ -721,6 +851,12 @@ fn do_tcp_setsockopt(
             }
             options.tcp.set_maxseg(*maxseg);
         },
+        tcp_cork: Cork => {
+            let cork = tcp_cork.get().unwrap();
+            options.tcp.set_cork(*cork);
+            let set_cork = |raw_socket: &dyn RawTcpSetOption| raw_socket.set_corked(*cork);
+            return Ok(state.set_raw_option(set_cork).unwrap_or(NeedIfacePoll::FALSE));
+        },
         tcp_keep_idle: KeepIdle => {
             const MIN_KEEP_IDLE: u32 = 1;
             const MAX_KEEP_IDLE: u32 = 32767;
@@ -730,8 +866,29 @@ fn do_tcp_setsockopt(
                 return_errno_with_message!(Errno::EINVAL, "the keep idle time is out of bounds");
             }
             options.tcp.set_keep_idle(*keepidle);
+            return Ok(state.update_keep_alive(options));
+        },
+        tcp_keep_intvl: KeepIntvl => {
+            const MIN_KEEP_INTVL: u32 = 1;
+            const MAX_KEEP_INTVL: u32 = 32767;
 
-            // TODO: Track when the socket becomes idle to actually support keep idle.
+            let keepintvl = tcp_keep_intvl.get().unwrap();
+            if *keepintvl < MIN_KEEP_INTVL || *keepintvl > MAX_KEEP_INTVL {
+                return_errno_with_message!(Errno::EINVAL, "the keep interval is out of bounds");
+            }
+            options.tcp.set_keep_intvl(*keepintvl);
+            return Ok(state.update_keep_alive(options));
+        },
+        tcp_keep_cnt: KeepCnt => {
+            const MIN_KEEP_CNT: u32 = 1;
+            const MAX_KEEP_CNT: u32 = 127;
+
+            let keepcnt = tcp_keep_cnt.get().unwrap();
+            if *keepcnt < MIN_KEEP_CNT || *keepcnt > MAX_KEEP_CNT {
+                return_errno_with_message!(Errno::EINVAL, "the keep count is out of bounds");
+            }
+            options.tcp.set_keep_cnt(*keepcnt);
+            return Ok(state.update_keep_alive(options));
         },
         tcp_syn_cnt: SynCnt => {
             const MAX_TCP_SYN_CNT: u8 = 127;
@@ -749,6 +906,8 @@ fn do_tcp_setsockopt(
             }
             let retrans = Retrans::from_secs(seconds);
             options.tcp.set_defer_accept(retrans);
+            let defer_accept = options.raw().defer_accept;
+            state.set_raw_option(|raw_socket: &dyn RawTcpSetOption| raw_socket.set_defer_accept(defer_accept));
         },
         tcp_window_clamp: WindowClamp => {
             let window_clamp = tcp_window_clamp.get().unwrap();
@@ -762,6 +921,7 @@ fn do_tcp_setsockopt(
         tcp_congestion: Congestion => {
             let congestion = tcp_congestion.get().unwrap();
             options.tcp.set_congestion(*congestion);
+            state.set_raw_option(|raw_socket: &dyn RawTcpSetOption| raw_socket.set_congestion_control(congestion.to_raw()));
         },
         tcp_user_timeout: UserTimeout => {
             let user_timeout = tcp_user_timeout.get().unwrap();
@@ -796,6 +956,16 @@ impl State {
         }
     }
 
+    /// Updates the keep alive configuration after the TCP-level keep alive options are changed.
+    fn update_keep_alive(&self, options: &OptionSet) -> NeedIfacePoll {
+        let keep_alive = options.raw().keep_alive;
+        let set_keep_alive =
+            |raw_socket: &dyn RawTcpSetOption| raw_socket.set_keep_alive(keep_alive);
+
+        self.set_raw_option(set_keep_alive)
+            .unwrap_or(NeedIfacePoll::FALSE)
+    }
+
     fn iface(&self) -> Option<&Arc<Iface>> {
         match self {
             State::Init(_) => None,
@@ -806,17 +976,24 @@ impl State {
     }
 }
 
-impl SetSocketLevelOption for State {
+/// The socket state with the TCP-level options.
+///
+/// The TCP-level options are needed to set some socket-level options. For example, enabling
+/// `SO_KEEPALIVE` uses the intervals specified by `TCP_KEEPIDLE` and `TCP_KEEPINTVL`.
+struct StateWithTcpOptions<'a> {
+    state: &'a State,
+    tcp: TcpOptionSet,
+}
+
+impl SetSocketLevelOption for StateWithTcpOptions<'_> {
     fn set_keep_alive(&self, keep_alive: bool) -> NeedIfacePoll {
-        let interval = if keep_alive {
-            Some(KEEPALIVE_INTERVAL)
-        } else {
-            None
-        };
+        let keep_alive = keep_alive.then(|| self.tcp.keep_alive());
 
-        let set_keepalive = |raw_socket: &dyn RawTcpSetOption| raw_socket.set_keep_alive(interval);
+        let set_keepalive =
+            |raw_socket: &dyn RawTcpSetOption| raw_socket.set_keep_alive(keep_alive);
 
-        self.set_raw_option(set_keepalive)
+        self.state
+            .set_raw_option(set_keepalive)
             .unwrap_or(NeedIfacePoll::FALSE)
     }
 }
@@ -6,7 +6,10 @@ use crate::impl_socket_options;
 impl_socket_options!(
     pub struct NoDelay(bool);
     pub struct MaxSegment(u32);
+    pub struct Cork(bool);
     pub struct KeepIdle(u32);
+    pub struct KeepIntvl(u32);
+    pub struct KeepCnt(u32);
     pub struct SynCnt(u8);
     pub struct DeferAccept(u32);
     pub struct WindowClamp(u32);
@@ -14,10 +17,3 @@ impl_socket_options!(
     pub struct UserTimeout(u32);
     pub struct Inq(bool);
 );
-
-/// The keepalive interval.
-///
-/// The linux value can be found at `/proc/sys/net/ipv4/tcp_keepalive_intvl`,
-/// which is by default 75 seconds for most Linux distributions.
-pub(super) const KEEPALIVE_INTERVAL: aster_bigtcp::time::Duration =
-    aster_bigtcp::time::Duration::from_secs(75);
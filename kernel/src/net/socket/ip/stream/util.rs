@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::time::Duration;
+use aster_bigtcp::{
+    socket::{CongestionControl as RawCongestionControl, KeepAlive},
+    time::Duration,
+};
 
 use crate::prelude::*;
 
@@ -10,7 +13,10 @@ use crate::prelude::*;
 pub struct TcpOptionSet {
     no_delay: bool,
     maxseg: u32,
+    cork: bool,
     keep_idle: u32,
+    keep_intvl: u32,
+    keep_cnt: u32,
     syn_cnt: u8,
     defer_accept: Retrans,
     window_clamp: u32,
@@ -21,6 +27,8 @@ pub struct TcpOptionSet {
 
 pub const DEFAULT_MAXSEG: u32 = 536;
 pub const DEFAULT_KEEP_IDLE: u32 = 7200;
+pub const DEFAULT_KEEP_INTVL: u32 = 75;
+pub const DEFAULT_KEEP_CNT: u32 = 9;
 pub const DEFAULT_SYN_CNT: u8 = 6;
 pub const DEFAULT_WINDOW_CLAMP: u32 = 0x8000_0000;
 
@@ -29,7 +37,10 @@ impl TcpOptionSet {
         Self {
             no_delay: false,
             maxseg: DEFAULT_MAXSEG,
+            cork: false,
             keep_idle: DEFAULT_KEEP_IDLE,
+            keep_intvl: DEFAULT_KEEP_INTVL,
+            keep_cnt: DEFAULT_KEEP_CNT,
             syn_cnt: DEFAULT_SYN_CNT,
             defer_accept: Retrans(0),
             window_clamp: DEFAULT_WINDOW_CLAMP,
@@ -38,6 +49,16 @@ impl TcpOptionSet {
             receive_inq: false,
         }
     }
+
+    /// Returns the keep alive configuration specified by `TCP_KEEPIDLE`, `TCP_KEEPINTVL`, and
+    /// `TCP_KEEPCNT`.
+    pub fn keep_alive(&self) -> KeepAlive {
+        KeepAlive {
+            idle: Duration::from_secs(self.keep_idle as u64),
+            interval: Duration::from_secs(self.keep_intvl as u64),
+            probes: self.keep_cnt,
+        }
+    }
 }
 
 impl Default for TcpOptionSet {
@@ -129,4 +150,19 @@ impl CongestionControl {
             Self::Cubic => Self::CUBIC,
         }
     }
+
+    pub(super) fn from_raw(raw: RawCongestionControl) -> Option<Self> {
+        match raw {
+            RawCongestionControl::None => None,
+            RawCongestionControl::Reno => Some(Self::Reno),
+            RawCongestionControl::Cubic => Some(Self::Cubic),
+        }
+    }
+
+    pub(super) fn to_raw(self) -> RawCongestionControl {
+        match self {
+            Self::Reno => RawCongestionControl::Reno,
+            Self::Cubic => RawCongestionControl::Cubic,
+        }
+    }
 }
@@ -1,9 +1,17 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_rights::Rights;
+
 use self::options::SocketOption;
+pub(in crate::net) use self::util::timestamp::RealTimePacketClock;
 pub use self::util::{
-    options::LingerOption, send_recv_flags::SendRecvFlags, shutdown_cmd::SockShutdownCmd,
-    socket_addr::SocketAddr, MessageHeader,
+    filter::{CSockFilter, SocketFilter},
+    options::LingerOption,
+    send_recv_flags::SendRecvFlags,
+    shutdown_cmd::SockShutdownCmd,
+    socket_addr::SocketAddr,
+    timestamp::TimestampingFlags,
+    ControlMessage, MessageHeader,
 };
 use crate::{
     fs::{
@@ -12,16 +20,19 @@ use crate::{
     },
     prelude::*,
     util::{MultiRead, MultiWrite},
+    vm::vmo::Vmo,
 };
 
 pub mod ip;
 pub mod netlink;
 pub mod options;
+pub mod packet;
 pub mod unix;
 mod util;
 pub mod vsock;
 
 mod private {
+    use super::SendRecvFlags;
     use crate::{events::IoEvents, prelude::*, process::signal::Pollable};
 
     /// Common methods for sockets, but private to the network module.
@@ -53,6 +64,28 @@ mod private {
                 self.wait_events(events, None, try_op)
             }
         }
+
+        /// Blocks until some events occur to complete sending or receiving messages.
+        ///
+        /// This method is the same as [`Self::block_on`], except that it will not block if
+        /// `flags` contains [`SendRecvFlags::MSG_DONTWAIT`].
+        #[track_caller]
+        fn block_on_msg<F, R>(
+            &self,
+            flags: SendRecvFlags,
+            events: IoEvents,
+            mut try_op: F,
+        ) -> Result<R>
+        where
+            Self: Sized,
+            F: FnMut() -> Result<R>,
+        {
+            if flags.contains(SendRecvFlags::MSG_DONTWAIT) {
+                try_op()
+            } else {
+                self.block_on(events, try_op)
+            }
+        }
     }
 }
 
@@ -123,6 +156,14 @@ pub trait Socket: private::SocketPrivate + Send + Sync {
         writers: &mut dyn MultiWrite,
         flags: SendRecvFlags,
     ) -> Result<(usize, MessageHeader)>;
+
+    /// Returns the VMO to map when the socket is mapped at `offset` by `mmap`,
+    /// along with the corresponding offset in the VMO.
+    ///
+    /// See also [`FileLike::mmap_vmo`].
+    fn mmap_vmo(&self, _offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        return_errno_with_message!(Errno::ENODEV, "mmap is not supported");
+    }
 }
 
 impl<T: Socket + 'static> FileLike for T {
@@ -136,10 +177,7 @@ impl<T: Socket + 'static> FileLike for T {
         // TODO: Set correct flags
         self.sendmsg(
             reader,
-            MessageHeader {
-                addr: None,
-                control_message: None,
-            },
+            MessageHeader::new(None, Vec::new()),
             SendRecvFlags::empty(),
         )
     }
@@ -163,6 +201,10 @@ impl<T: Socket + 'static> FileLike for T {
         Ok(())
     }
 
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        Socket::mmap_vmo(self, offset)
+    }
+
     fn as_socket(&self) -> Option<&dyn Socket> {
         Some(self)
     }
@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Sub,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{
+    message::CnMessage,
+    proc_events::{self, CN_IDX_PROC},
+};
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        netlink::{message::ProtocolSegment, table::BoundHandle, NetlinkSocketAddr},
+        util::datagram_common,
+        SendRecvFlags,
+    },
+    prelude::*,
+    process::signal::Pollee,
+    util::{MultiRead, MultiWrite},
+};
+
+pub(super) struct BoundNetlinkConnector {
+    handle: BoundHandle,
+    remote_addr: NetlinkSocketAddr,
+    receive_queue: Arc<ReceiveQueue>,
+    /// Whether the socket listens to the process events.
+    is_listening: AtomicBool,
+}
+
+impl BoundNetlinkConnector {
+    pub(super) fn new(handle: BoundHandle, pollee: &Pollee) -> Self {
+        let receive_queue = Arc::new(ReceiveQueue::new(pollee.clone()));
+
+        // The group IDs start from one, so group `n` is represented by bit `n - 1`.
+        let groups = handle.addr().groups().as_u32();
+        if groups & (1 << (CN_IDX_PROC - 1)) != 0 {
+            proc_events::join_group(&receive_queue);
+        }
+
+        Self {
+            handle,
+            remote_addr: NetlinkSocketAddr::new_unspecified(),
+            receive_queue,
+            is_listening: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Drop for BoundNetlinkConnector {
+    fn drop(&mut self) {
+        proc_events::on_socket_release(&self.is_listening);
+    }
+}
+
+impl datagram_common::Bound for BoundNetlinkConnector {
+    type Endpoint = NetlinkSocketAddr;
+
+    fn local_endpoint(&self) -> Self::Endpoint {
+        self.handle.addr()
+    }
+
+    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
+        Some(&self.remote_addr)
+    }
+
+    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
+        self.remote_addr = *endpoint;
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: &Self::Endpoint,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        if *remote != NetlinkSocketAddr::new_unspecified() {
+            return_errno_with_message!(
+                Errno::ECONNREFUSED,
+                "sending netlink connector messages to user space is not supported"
+            );
+        }
+
+        let mut cnmsg = {
+            let sum_lens = reader.sum_lens();
+
+            match CnMessage::read_from(reader) {
+                Ok(cnmsg) => cnmsg,
+                Err(e) if e.error() == Errno::EFAULT => return Err(e),
+                Err(e) => {
+                    // Like the route sockets, malformed messages are silently ignored.
+                    warn!("failed to send netlink message: {:?}", e);
+                    return Ok(sum_lens);
+                }
+            }
+        };
+
+        let local_port = self.handle.port();
+        for segment in cnmsg.segments_mut() {
+            let header = segment.header_mut();
+            if header.pid == 0 {
+                header.pid = local_port;
+            }
+        }
+
+        for segment in cnmsg.segments() {
+            proc_events::handle_request(segment, &self.is_listening);
+        }
+
+        Ok(cnmsg.total_len())
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, NetlinkSocketAddr)> {
+        // TODO: Deal with other flags. Only MSG_PEEK is handled here.
+        if !flags.sub(SendRecvFlags::MSG_PEEK).is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let len = self
+            .receive_queue
+            .pop(writer, flags.contains(SendRecvFlags::MSG_PEEK))?;
+
+        // The messages can only come from the kernel.
+        let remote = NetlinkSocketAddr::new_unspecified();
+
+        Ok((len, remote))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        IoEvents::OUT | self.receive_queue.check_io_events()
+    }
+}
+
+/// The queue of the messages that a netlink connector socket receives.
+pub(super) struct ReceiveQueue {
+    messages: Mutex<VecDeque<CnMessage>>,
+    /// Whether some messages are dropped because the queue is full.
+    has_overrun: AtomicBool,
+    pollee: Pollee,
+}
+
+/// The maximum number of the messages in a receive queue.
+const MAX_QUEUED_MESSAGES: usize = 1024;
+
+impl ReceiveQueue {
+    fn new(pollee: Pollee) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            has_overrun: AtomicBool::new(false),
+            pollee,
+        }
+    }
+
+    /// Pushes a message to the queue.
+    ///
+    /// If the queue is full, the message is dropped and the next receive operation will fail
+    /// with [`Errno::ENOBUFS`], as in Linux.
+    pub(super) fn push(&self, message: CnMessage) {
+        let mut messages = self.messages.lock();
+        if messages.len() >= MAX_QUEUED_MESSAGES {
+            self.has_overrun.store(true, Ordering::Relaxed);
+            drop(messages);
+            self.pollee.notify(IoEvents::IN | IoEvents::ERR);
+            return;
+        }
+
+        messages.push_back(message);
+        drop(messages);
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Pops a message from the queue and writes it to `writer`.
+    ///
+    /// The message is truncated if `writer` is not large enough.
+    fn pop(&self, writer: &mut dyn MultiWrite, is_peek: bool) -> Result<usize> {
+        if self.has_overrun.swap(false, Ordering::Relaxed) {
+            return_errno_with_message!(Errno::ENOBUFS, "some messages have been dropped");
+        }
+
+        let mut messages = self.messages.lock();
+
+        let Some(message) = messages.front() else {
+            return_errno_with_message!(Errno::EAGAIN, "nothing to receive");
+        };
+
+        let len = message.total_len().min(writer.sum_lens());
+        message.write_to(writer)?;
+
+        if !is_peek {
+            messages.pop_front().unwrap();
+        }
+
+        Ok(len)
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if self.has_overrun.load(Ordering::Relaxed) {
+            return IoEvents::IN | IoEvents::ERR;
+        }
+
+        if self.messages.lock().is_empty() {
+            IoEvents::empty()
+        } else {
+            IoEvents::IN
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink connector message types.
+
+use align_ext::AlignExt;
+use ostd::mm::{VmReader, VmWriter};
+
+use crate::{
+    net::socket::netlink::message::{
+        CMsgSegHdr, CSegmentType, Message, ProtocolSegment, NLMSG_ALIGN,
+    },
+    prelude::*,
+    util::{MultiRead, MultiWrite},
+};
+
+/// A netlink connector message.
+pub(super) type CnMessage = Message<CnSegment>;
+
+/// The ID of a connector service.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/connector.h#L53>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod)]
+pub(super) struct CbId {
+    pub(super) idx: u32,
+    pub(super) val: u32,
+}
+
+/// `cn_msg` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/connector.h#L58>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CCnMsg {
+    pub(super) id: CbId,
+    pub(super) seq: u32,
+    pub(super) ack: u32,
+    /// Length of the following data
+    pub(super) len: u16,
+    pub(super) flags: u16,
+}
+
+/// A segment of a netlink connector message.
+///
+/// Each segment carries a [`CCnMsg`] followed by the data of the connector service.
+#[derive(Debug, Clone)]
+pub(super) struct CnSegment {
+    header: CMsgSegHdr,
+    cn_msg: CCnMsg,
+    data: Vec<u8>,
+}
+
+impl CnSegment {
+    /// Creates a new segment sent from the kernel.
+    pub(super) fn new(id: CbId, seq: u32, ack: u32, data: Vec<u8>) -> Self {
+        let len = size_of::<CMsgSegHdr>() + size_of::<CCnMsg>() + data.len();
+        let header = CMsgSegHdr {
+            len: len as u32,
+            // Like Linux, the kernel sends each connector message as a standalone segment.
+            type_: CSegmentType::DONE as _,
+            flags: 0,
+            seq,
+            pid: 0,
+        };
+        let cn_msg = CCnMsg {
+            id,
+            seq,
+            ack,
+            len: data.len() as u16,
+            flags: 0,
+        };
+
+        Self {
+            header,
+            cn_msg,
+            data,
+        }
+    }
+
+    pub(super) fn cn_msg(&self) -> &CCnMsg {
+        &self.cn_msg
+    }
+
+    pub(super) fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl ProtocolSegment for CnSegment {
+    fn header(&self) -> &CMsgSegHdr {
+        &self.header
+    }
+
+    fn header_mut(&mut self) -> &mut CMsgSegHdr {
+        &mut self.header
+    }
+
+    fn read_from(reader: &mut dyn MultiRead) -> Result<Self> {
+        let header = reader.read_val::<CMsgSegHdr>()?;
+
+        let remaining_len = (header.len as usize)
+            .checked_sub(size_of::<CMsgSegHdr>() + size_of::<CCnMsg>())
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the message length is too small"))?;
+        if reader.sum_lens() < remaining_len + size_of::<CCnMsg>() {
+            return_errno_with_message!(Errno::EINVAL, "the reader length is too small");
+        }
+
+        let cn_msg = reader.read_val::<CCnMsg>()?;
+        if cn_msg.len as usize > remaining_len {
+            return_errno_with_message!(Errno::EINVAL, "the connector data length is too large");
+        }
+
+        let mut data = vec![0u8; cn_msg.len as usize];
+        reader.read(&mut VmWriter::from(data.as_mut_slice()))?;
+
+        // Skip the rest of the segment, including the padding bytes.
+        let skipped_len = (remaining_len - data.len())
+            .align_up(NLMSG_ALIGN)
+            .min(reader.sum_lens());
+        reader.skip(skipped_len);
+
+        Ok(Self {
+            header,
+            cn_msg,
+            data,
+        })
+    }
+
+    fn write_to(&self, writer: &mut dyn MultiWrite) -> Result<()> {
+        let total_len = (self.header.len as usize).align_up(NLMSG_ALIGN);
+        let mut bytes = Vec::with_capacity(total_len);
+        bytes.extend_from_slice(self.header.as_bytes());
+        bytes.extend_from_slice(self.cn_msg.as_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes.resize(total_len, 0);
+
+        // The segment is truncated if the writer is not large enough.
+        writer.write(&mut VmReader::from(bytes.as_slice()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The process events connector.
+//!
+//! The connector reports the lifecycle events of the processes and the threads (e.g., fork,
+//! exec, and exit) to the sockets in the [`CN_IDX_PROC`] multicast group. The events are only
+//! generated after some socket asks for them with a listen request, so the process operations
+//! are not slowed down if no one is interested.
+//!
+//! Each event carries a sequence number that is increased by one for each event, so the user
+//! space can detect the events that are lost because its receive queue overflows.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/connector/cn_proc.c>.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use aster_time::read_monotonic_time;
+use ostd::cpu::current_cpu_racy;
+
+use super::{
+    bound::ReceiveQueue,
+    message::{CbId, CnMessage, CnSegment},
+};
+use crate::{
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet, posix_thread::AsPosixThread, signal::sig_num::SigNum,
+        Gid, Pid, Uid,
+    },
+    thread::Tid,
+};
+
+/// The index of the process events connector, which is also its multicast group.
+pub(super) const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CB_ID: CbId = CbId {
+    idx: CN_IDX_PROC,
+    val: CN_VAL_PROC,
+};
+
+/// The number of the sockets that listen to the process events.
+static NR_LISTENERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The sequence number of the next process event.
+static NEXT_SEQ: AtomicU32 = AtomicU32::new(0);
+
+/// The receive queues of the sockets in the [`CN_IDX_PROC`] multicast group.
+static GROUP_MEMBERS: Mutex<Vec<Weak<ReceiveQueue>>> = Mutex::new(Vec::new());
+
+/// Adds the receive queue of a socket to the [`CN_IDX_PROC`] multicast group.
+///
+/// The queue leaves the group automatically when it is dropped.
+pub(super) fn join_group(queue: &Arc<ReceiveQueue>) {
+    let mut members = GROUP_MEMBERS.lock();
+    members.retain(|member| member.strong_count() > 0);
+    members.push(Arc::downgrade(queue));
+}
+
+/// Sends a segment to all the sockets in the [`CN_IDX_PROC`] multicast group.
+fn broadcast(segment: CnSegment) {
+    // Collect the queues first to avoid holding the lock while pushing the messages.
+    let members: Vec<_> = GROUP_MEMBERS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+
+    let Some((last, others)) = members.split_last() else {
+        return;
+    };
+    for member in others {
+        member.push(CnMessage::new(vec![segment.clone()]));
+    }
+    last.push(CnMessage::new(vec![segment]));
+}
+
+/// The types of the process events.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/cn_proc.h#L75>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy)]
+enum ProcEventType {
+    /// An acknowledgment of a listen or ignore request.
+    None = 0,
+    Fork = 1,
+    Exec = 2,
+    Uid = 4,
+    Gid = 0x40,
+    Exit = 0x8000_0000,
+}
+
+/// `proc_event` in Linux.
+///
+/// The event data is a union in Linux, whose largest member (i.e., the exit event) has six
+/// 32-bit fields.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/cn_proc.h#L94>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CProcEvent {
+    what: u32,
+    cpu: u32,
+    timestamp_ns: u64,
+    event_data: [u32; 6],
+}
+
+impl CProcEvent {
+    fn new(what: ProcEventType, cpu: u32, event_data: [u32; 6]) -> Self {
+        Self {
+            what: what as u32,
+            cpu,
+            timestamp_ns: read_monotonic_time().as_nanos() as u64,
+            event_data,
+        }
+    }
+}
+
+/// Reports a process event to the listening sockets.
+fn send_event(what: ProcEventType, event_data: [u32; 6]) {
+    if NR_LISTENERS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    let cpu = current_cpu_racy().as_usize() as u32;
+    let event = CProcEvent::new(what, cpu, event_data);
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    broadcast(CnSegment::new(
+        PROC_CB_ID,
+        seq,
+        0,
+        event.as_bytes().to_vec(),
+    ));
+}
+
+/// Reports that a thread or a process is created.
+pub fn on_fork(parent_tid: Tid, parent_pid: Pid, child_tid: Tid, child_pid: Pid) {
+    send_event(
+        ProcEventType::Fork,
+        [parent_tid, parent_pid, child_tid, child_pid, 0, 0],
+    );
+}
+
+/// Reports that a process executes a new program.
+pub fn on_exec(tid: Tid, pid: Pid) {
+    send_event(ProcEventType::Exec, [tid, pid, 0, 0, 0, 0]);
+}
+
+/// Reports that the real or effective user ID of a thread changes.
+pub fn on_uid_change(tid: Tid, pid: Pid, ruid: Uid, euid: Uid) {
+    send_event(
+        ProcEventType::Uid,
+        [tid, pid, ruid.into(), euid.into(), 0, 0],
+    );
+}
+
+/// Reports that the real or effective group ID of a thread changes.
+pub fn on_gid_change(tid: Tid, pid: Pid, rgid: Gid, egid: Gid) {
+    send_event(
+        ProcEventType::Gid,
+        [tid, pid, rgid.into(), egid.into(), 0, 0],
+    );
+}
+
+/// Reports that a thread exits.
+///
+/// The exit code is encoded as the status reported by `wait`. The exit signal is the signal
+/// sent to the parent, which is absent for the threads other than the main thread.
+pub fn on_exit(
+    tid: Tid,
+    pid: Pid,
+    exit_code: u32,
+    exit_signal: Option<SigNum>,
+    parent_tid: Tid,
+    parent_pid: Pid,
+) {
+    let exit_signal = exit_signal.map_or(u32::MAX, |signum| signum.as_u8() as u32);
+    send_event(
+        ProcEventType::Exit,
+        [tid, pid, exit_code, exit_signal, parent_tid, parent_pid],
+    );
+}
+
+/// The operations that the user space requests.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/cn_proc.h#L30>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, TryFromInt)]
+enum ProcCnMcastOp {
+    Listen = 1,
+    Ignore = 2,
+}
+
+/// Handles a request to the process events connector.
+///
+/// `is_listening` records whether the requesting socket listens to the process events. Requests
+/// to other connectors are ignored.
+pub(super) fn handle_request(segment: &CnSegment, is_listening: &AtomicBool) {
+    let cn_msg = segment.cn_msg();
+    if cn_msg.id != PROC_CB_ID {
+        debug!("the connector does not exist: {:?}", cn_msg.id);
+        return;
+    }
+    let Ok(op) = <[u8; 4]>::try_from(segment.data()) else {
+        return;
+    };
+
+    let result = if !is_capable(CapSet::NET_ADMIN) {
+        Err(Errno::EPERM)
+    } else {
+        match ProcCnMcastOp::try_from(u32::from_ne_bytes(op)) {
+            Ok(ProcCnMcastOp::Listen) => {
+                if !is_listening.swap(true, Ordering::Relaxed) {
+                    NR_LISTENERS.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+            Ok(ProcCnMcastOp::Ignore) => {
+                on_socket_release(is_listening);
+                Ok(())
+            }
+            Err(_) => Err(Errno::EINVAL),
+        }
+    };
+
+    // Like Linux, the acknowledgment is sent only if some socket still listens.
+    if NR_LISTENERS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let err = result.err().map_or(0, |errno| errno as u32);
+    let event = CProcEvent::new(ProcEventType::None, u32::MAX, [err, 0, 0, 0, 0, 0]);
+    broadcast(CnSegment::new(
+        PROC_CB_ID,
+        cn_msg.seq,
+        cn_msg.ack.wrapping_add(1),
+        event.as_bytes().to_vec(),
+    ));
+}
+
+/// Stops the socket from listening to the process events.
+pub(super) fn on_socket_release(is_listening: &AtomicBool) {
+    if is_listening.swap(false, Ordering::Relaxed) {
+        NR_LISTENERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn is_capable(cap: CapSet) -> bool {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+    credentials.permitted_capset().contains(cap) && credentials.effective_capset().contains(cap)
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::bound::BoundNetlinkConnector;
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        netlink::{table::NETLINK_SOCKET_TABLE, NetlinkSocketAddr, StandardNetlinkProtocol},
+        util::datagram_common,
+    },
+    prelude::*,
+    process::signal::Pollee,
+};
+
+pub(super) struct UnboundNetlinkConnector {
+    _private: (),
+}
+
+impl UnboundNetlinkConnector {
+    pub(super) const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl datagram_common::Unbound for UnboundNetlinkConnector {
+    type Endpoint = NetlinkSocketAddr;
+    type BindOptions = ();
+
+    type Bound = BoundNetlinkConnector;
+
+    fn bind(
+        &mut self,
+        endpoint: &Self::Endpoint,
+        pollee: &Pollee,
+        _options: Self::BindOptions,
+    ) -> Result<BoundNetlinkConnector> {
+        let bound_handle =
+            NETLINK_SOCKET_TABLE.bind(StandardNetlinkProtocol::CONNECTOR as _, endpoint)?;
+
+        Ok(BoundNetlinkConnector::new(bound_handle, pollee))
+    }
+
+    fn bind_ephemeral(
+        &mut self,
+        _remote_endpoint: &Self::Endpoint,
+        pollee: &Pollee,
+    ) -> Result<Self::Bound> {
+        let bound_handle = NETLINK_SOCKET_TABLE.bind(
+            StandardNetlinkProtocol::CONNECTOR as _,
+            &NetlinkSocketAddr::new_unspecified(),
+        )?;
+
+        Ok(BoundNetlinkConnector::new(bound_handle, pollee))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        IoEvents::OUT
+    }
+}
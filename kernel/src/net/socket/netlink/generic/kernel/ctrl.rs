@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle requests to the `nlctrl` family, which resolves the IDs of the families.
+
+use super::util::{finish_response, is_dump_request, new_response_body, new_response_header};
+use crate::{
+    net::socket::netlink::{
+        generic::message::{
+            CtrlAttr, CtrlCmd, CtrlSegment, GenlSegment, GENL_ID_CTRL, GENL_ID_WIREGUARD,
+        },
+        message::CMsgSegHdr,
+    },
+    prelude::*,
+};
+
+/// A family that is supported by the kernel.
+struct Family {
+    id: u16,
+    name: &'static str,
+    version: u32,
+    max_attr: u32,
+}
+
+/// The supported families.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netlink/genetlink.c>,
+/// <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+static FAMILIES: [Family; 2] = [
+    Family {
+        id: GENL_ID_CTRL,
+        name: "nlctrl",
+        version: 2,
+        // CTRL_ATTR_OP
+        max_attr: 10,
+    },
+    Family {
+        id: GENL_ID_WIREGUARD,
+        name: "wireguard",
+        version: 1,
+        // WGDEVICE_A_PEERS
+        max_attr: 8,
+    },
+];
+
+pub(super) fn do_ctrl(request_segment: &CtrlSegment) -> Result<Vec<GenlSegment>> {
+    match CtrlCmd::try_from(request_segment.body().cmd) {
+        Ok(CtrlCmd::GETFAMILY) => do_get_family(request_segment),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported"),
+    }
+}
+
+fn do_get_family(request_segment: &CtrlSegment) -> Result<Vec<GenlSegment>> {
+    let request_header = request_segment.header();
+
+    let dump_all = is_dump_request(request_header);
+    let families: Vec<&Family> = if dump_all {
+        FAMILIES.iter().collect()
+    } else {
+        vec![find_family(request_segment.attrs())?]
+    };
+
+    let mut response_segments = families
+        .into_iter()
+        .map(|family| GenlSegment::Ctrl(family_to_new_family(request_header, family)))
+        .collect();
+    finish_response(request_header, dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+/// Finds the family by its ID or its name, preferring the ID like Linux.
+fn find_family(attrs: &[CtrlAttr]) -> Result<&'static Family> {
+    let family = if let Some(id) = attrs.iter().find_map(|attr| match attr {
+        CtrlAttr::FamilyId(id) => Some(*id),
+        _ => None,
+    }) {
+        FAMILIES.iter().find(|family| family.id == id)
+    } else if let Some(name) = attrs.iter().find_map(|attr| match attr {
+        CtrlAttr::FamilyName(name) => Some(name),
+        _ => None,
+    }) {
+        FAMILIES
+            .iter()
+            .find(|family| family.name.as_bytes() == name.as_bytes())
+    } else {
+        return_errno_with_message!(Errno::EINVAL, "the family is not specified");
+    };
+
+    family.ok_or_else(|| Error::with_message(Errno::ENOENT, "the family does not exist"))
+}
+
+fn family_to_new_family(request_header: &CMsgSegHdr, family: &Family) -> CtrlSegment {
+    let header = new_response_header(request_header, GENL_ID_CTRL);
+    // The version of the `nlctrl` family.
+    let body = new_response_body(CtrlCmd::NEWFAMILY as u8, 2);
+
+    let attrs = vec![
+        CtrlAttr::FamilyName(CString::new(family.name).unwrap()),
+        CtrlAttr::FamilyId(family.id),
+        CtrlAttr::Version(family.version),
+        // No family has a family-specific header.
+        CtrlAttr::HdrSize(0),
+        CtrlAttr::MaxAttr(family.max_attr),
+    ];
+
+    CtrlSegment::new(header, body, attrs)
+}
@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines the kernel socket,
+//! which is responsible for handling requests from user space.
+//!
+//! Each request is dispatched to the family identified by the segment type, which then handles
+//! the command in the generic netlink header.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netlink/genetlink.c>.
+
+use core::marker::PhantomData;
+
+use super::message::{GenlMessage, GenlSegment};
+use crate::{
+    net::socket::netlink::message::{ErrorSegment, ProtocolSegment, SegHdrCommonFlags},
+    prelude::*,
+};
+
+mod ctrl;
+mod util;
+mod wireguard;
+
+pub(super) struct NetlinkGenericKernelSocket {
+    _private: PhantomData<()>,
+}
+
+impl NetlinkGenericKernelSocket {
+    const fn new() -> Self {
+        Self {
+            _private: PhantomData,
+        }
+    }
+
+    pub(super) fn request<F: FnMut(GenlMessage)>(
+        &self,
+        request: &GenlMessage,
+        mut consume_response: F,
+    ) {
+        debug!("generic netlink request: {:?}", request);
+
+        for segment in request.segments() {
+            let request_header = segment.header();
+
+            let response_segments = match segment {
+                GenlSegment::Ctrl(request_segment) => ctrl::do_ctrl(request_segment),
+                GenlSegment::WireGuard(request_segment) => wireguard::do_wireguard(request_segment),
+                GenlSegment::Unsupported(_) | GenlSegment::Done(_) | GenlSegment::Error(_) => {
+                    warn!("unsupported family ID: {:#x}", request_header.type_);
+                    Err(Error::with_message(
+                        Errno::ENOENT,
+                        "the family does not exist",
+                    ))
+                }
+            };
+
+            let response = match response_segments {
+                // Requests that modify the kernel state have no response segments. Their results
+                // are reported only if the `ACK` flag is set.
+                Ok(segments) if segments.is_empty() => {
+                    let flags = SegHdrCommonFlags::from_bits_truncate(request_header.flags);
+                    if !flags.contains(SegHdrCommonFlags::ACK) {
+                        continue;
+                    }
+                    let ack_segment = ErrorSegment::new_from_request(request_header, None);
+                    GenlMessage::new(vec![GenlSegment::Error(ack_segment)])
+                }
+                Ok(segments) => GenlMessage::new(segments),
+                Err(error) => {
+                    let err_segment = ErrorSegment::new_from_request(request_header, Some(error));
+                    GenlMessage::new(vec![GenlSegment::Error(err_segment)])
+                }
+            };
+
+            debug!("generic netlink response: {:?}", response);
+
+            consume_response(response);
+        }
+    }
+}
+
+/// FIXME: NETLINK_GENERIC_KERNEL should be a per-network namespace socket
+static NETLINK_GENERIC_KERNEL: NetlinkGenericKernelSocket = NetlinkGenericKernelSocket::new();
+
+pub(super) fn get_netlink_generic_kernel() -> &'static NetlinkGenericKernelSocket {
+    &NETLINK_GENERIC_KERNEL
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    net::socket::netlink::{
+        generic::message::{GenlMsgBody, GenlSegment},
+        message::{CMsgSegHdr, DoneSegment, GetRequestFlags, ProtocolSegment, SegHdrCommonFlags},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// Returns whether a get request asks for all entries.
+pub fn is_dump_request(request_header: &CMsgSegHdr) -> bool {
+    let flags = GetRequestFlags::from_bits_truncate(request_header.flags);
+    flags.contains(GetRequestFlags::DUMP)
+}
+
+/// Finishes a response message.
+pub fn finish_response(
+    request_header: &CMsgSegHdr,
+    dump_all: bool,
+    response_segments: &mut Vec<GenlSegment>,
+) {
+    if !dump_all {
+        assert_eq!(response_segments.len(), 1);
+        return;
+    }
+    append_done_segment(request_header, response_segments);
+    add_multi_flag(response_segments);
+}
+
+/// Appends a done segment as the last segment of the provided segments.
+fn append_done_segment(request_header: &CMsgSegHdr, response_segments: &mut Vec<GenlSegment>) {
+    let done_segment = DoneSegment::new_from_request(request_header, None);
+    response_segments.push(GenlSegment::Done(done_segment));
+}
+
+/// Adds the `MULTI` flag to all segments in `segments`.
+fn add_multi_flag(response_segments: &mut Vec<GenlSegment>) {
+    for segment in response_segments.iter_mut() {
+        let header = segment.header_mut();
+        let mut flags = SegHdrCommonFlags::from_bits_truncate(header.flags);
+        flags |= SegHdrCommonFlags::MULTI;
+        header.flags = flags.bits();
+    }
+}
+
+/// Creates the header of a response segment of the family.
+pub fn new_response_header(request_header: &CMsgSegHdr, family_id: u16) -> CMsgSegHdr {
+    CMsgSegHdr {
+        len: 0,
+        type_: family_id,
+        flags: SegHdrCommonFlags::empty().bits(),
+        seq: request_header.seq,
+        pid: request_header.pid,
+    }
+}
+
+/// Creates the body of a response segment.
+pub fn new_response_body(cmd: u8, version: u8) -> GenlMsgBody {
+    GenlMsgBody { cmd, version }
+}
+
+/// Checks whether the current thread is allowed to access the network configurations.
+pub fn check_net_admin() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+    if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "accessing the network configurations requires `CAP_NET_ADMIN`"
+        );
+    }
+
+    Ok(())
+}
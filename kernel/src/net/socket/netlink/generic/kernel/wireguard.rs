@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle requests to the `wireguard` family, which configures WireGuard devices.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/drivers/net/wireguard/netlink.c>.
+
+use aster_bigtcp::wire::{IpAddress, IpCidr, IpEndpoint};
+
+use super::util::{
+    check_net_admin, finish_response, is_dump_request, new_response_body, new_response_header,
+};
+use crate::{
+    net::{
+        iface::{
+            find_wireguard, iter_all_ifaces, Iface, WireGuard, WireGuardConfig,
+            WireGuardPeerConfig, WireGuardPeerStatus,
+        },
+        socket::{
+            netlink::{
+                generic::message::{
+                    ArrayElem, GenlSegment, IpAddrBytes, Nested, WgAllowedIpAttr, WgCmd,
+                    WgDeviceAttr, WgDeviceFlags, WgPeerAttr, WgPeerFlags, WgSegment,
+                    GENL_ID_WIREGUARD,
+                },
+                message::CMsgSegHdr,
+            },
+            SocketAddr,
+        },
+    },
+    prelude::*,
+    time::timespec_t,
+    util::net::{socket_addr_from_c_bytes, socket_addr_to_c_bytes, CSocketAddrFamily},
+};
+
+/// The version of the `wireguard` family.
+const WG_GENL_VERSION: u8 = 1;
+
+/// The version of the WireGuard protocol, which is the only version that is supported.
+const PROTOCOL_VERSION: u32 = 1;
+
+pub(super) fn do_wireguard(request_segment: &WgSegment) -> Result<Vec<GenlSegment>> {
+    // Like Linux, both commands require `CAP_NET_ADMIN`, since the private key can be read.
+    check_net_admin()?;
+
+    match WgCmd::try_from(request_segment.body().cmd) {
+        Ok(WgCmd::GET_DEVICE) => do_get_device(request_segment),
+        Ok(WgCmd::SET_DEVICE) => do_set_device(request_segment),
+        Err(_) => return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported"),
+    }
+}
+
+fn do_get_device(request_segment: &WgSegment) -> Result<Vec<GenlSegment>> {
+    let request_header = request_segment.header();
+
+    // Like Linux, the device can only be dumped.
+    if !is_dump_request(request_header) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the device can only be dumped");
+    }
+
+    let (iface, wireguard) = lookup_device(request_segment.attrs())?;
+
+    // FIXME: Linux splits the peers into multiple segments if they do not fit in one segment.
+    // We always put all the peers in one segment.
+    let mut response_segments = vec![GenlSegment::WireGuard(device_to_segment(
+        request_header,
+        &iface,
+        &wireguard,
+    ))];
+    finish_response(request_header, true, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn do_set_device(request_segment: &WgSegment) -> Result<Vec<GenlSegment>> {
+    let (_, wireguard) = lookup_device(request_segment.attrs())?;
+
+    let mut config = WireGuardConfig::default();
+    for attr in request_segment.attrs() {
+        match attr {
+            WgDeviceAttr::Flags(flags) => {
+                let Some(flags) = WgDeviceFlags::from_bits(*flags) else {
+                    return_errno_with_message!(Errno::EOPNOTSUPP, "the device flags are invalid");
+                };
+                config.replace_peers = flags.contains(WgDeviceFlags::REPLACE_PEERS);
+            }
+            WgDeviceAttr::PrivateKey(private_key) => config.private_key = Some(*private_key),
+            WgDeviceAttr::ListenPort(listen_port) => config.listen_port = Some(*listen_port),
+            WgDeviceAttr::Fwmark(fwmark) => config.fwmark = Some(*fwmark),
+            WgDeviceAttr::Peers(peers) => {
+                for ArrayElem(peer) in peers.parse::<ArrayElem>()? {
+                    config.peers.push(parse_peer(&peer.parse::<WgPeerAttr>()?)?);
+                }
+            }
+            WgDeviceAttr::IfIndex(_) | WgDeviceAttr::IfName(_) | WgDeviceAttr::PublicKey(_) => {}
+        }
+    }
+
+    wireguard.configure(config)?;
+
+    Ok(Vec::new())
+}
+
+/// Finds the WireGuard device by the iface index or the iface name.
+///
+/// Like Linux, exactly one of them must be specified.
+fn lookup_device(attrs: &[WgDeviceAttr]) -> Result<(Arc<Iface>, Arc<WireGuard>)> {
+    let index = attrs.iter().find_map(|attr| match attr {
+        WgDeviceAttr::IfIndex(index) => Some(*index),
+        _ => None,
+    });
+    let name = attrs.iter().find_map(|attr| match attr {
+        WgDeviceAttr::IfName(name) => Some(name),
+        _ => None,
+    });
+
+    let iface = match (index, name) {
+        (Some(index), None) => iter_all_ifaces().find(|iface| iface.index() == index),
+        (None, Some(name)) => {
+            iter_all_ifaces().find(|iface| iface.name().as_bytes() == name.as_bytes())
+        }
+        _ => return_errno_with_message!(
+            Errno::EBADR,
+            "exactly one of the interface index and the interface name must be specified"
+        ),
+    };
+    let Some(iface) = iface else {
+        return_errno_with_message!(Errno::ENODEV, "the interface does not exist");
+    };
+
+    let Some(wireguard) = find_wireguard(iface.index()) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the interface is not a WireGuard device");
+    };
+
+    Ok((iface, wireguard))
+}
+
+fn parse_peer(attrs: &[WgPeerAttr]) -> Result<WireGuardPeerConfig> {
+    let Some(public_key) = attrs.iter().find_map(|attr| match attr {
+        WgPeerAttr::PublicKey(public_key) => Some(*public_key),
+        _ => None,
+    }) else {
+        return_errno_with_message!(Errno::EINVAL, "the public key of the peer is not specified");
+    };
+
+    let mut config = WireGuardPeerConfig {
+        public_key,
+        remove: false,
+        update_only: false,
+        preshared_key: None,
+        endpoint: None,
+        persistent_keepalive_interval: None,
+        replace_allowed_ips: false,
+        allowed_ips: Vec::new(),
+    };
+
+    for attr in attrs {
+        match attr {
+            WgPeerAttr::Flags(flags) => {
+                let Some(flags) = WgPeerFlags::from_bits(*flags) else {
+                    return_errno_with_message!(Errno::EOPNOTSUPP, "the peer flags are invalid");
+                };
+                config.remove = flags.contains(WgPeerFlags::REMOVE_ME);
+                config.update_only = flags.contains(WgPeerFlags::UPDATE_ONLY);
+                config.replace_allowed_ips = flags.contains(WgPeerFlags::REPLACE_ALLOWEDIPS);
+            }
+            WgPeerAttr::PresharedKey(preshared_key) => config.preshared_key = Some(*preshared_key),
+            WgPeerAttr::Endpoint(endpoint) => {
+                // Like Linux, an invalid endpoint is ignored.
+                config.endpoint = socket_addr_from_c_bytes(endpoint)
+                    .and_then(IpEndpoint::try_from)
+                    .ok();
+            }
+            WgPeerAttr::PersistentKeepaliveInterval(interval) => {
+                config.persistent_keepalive_interval = Some(*interval)
+            }
+            WgPeerAttr::AllowedIps(allowed_ips) => {
+                for ArrayElem(allowed_ip) in allowed_ips.parse::<ArrayElem>()? {
+                    let cidr = parse_allowed_ip(&allowed_ip.parse::<WgAllowedIpAttr>()?)?;
+                    config.allowed_ips.push(cidr);
+                }
+            }
+            WgPeerAttr::ProtocolVersion(version) => {
+                if *version != 0 && *version != PROTOCOL_VERSION {
+                    return_errno_with_message!(
+                        Errno::EPROTONOSUPPORT,
+                        "the protocol version is not supported"
+                    );
+                }
+            }
+            WgPeerAttr::PublicKey(_)
+            | WgPeerAttr::LastHandshakeTime(_)
+            | WgPeerAttr::RxBytes(_)
+            | WgPeerAttr::TxBytes(_) => {}
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_allowed_ip(attrs: &[WgAllowedIpAttr]) -> Result<IpCidr> {
+    let mut family = None;
+    let mut addr = None;
+    let mut prefix_len = None;
+    for attr in attrs {
+        match attr {
+            WgAllowedIpAttr::Family(value) => family = Some(*value as i32),
+            WgAllowedIpAttr::IpAddr(value) => addr = Some(*value),
+            WgAllowedIpAttr::CidrMask(value) => prefix_len = Some(*value),
+        }
+    }
+
+    let (Some(family), Some(addr), Some(prefix_len)) = (family, addr, prefix_len) else {
+        return_errno_with_message!(Errno::EINVAL, "the allowed IP is incomplete");
+    };
+
+    // The address must be in the family, and the prefix must not be longer than the address.
+    let is_valid = match (CSocketAddrFamily::try_from(family), addr) {
+        (Ok(CSocketAddrFamily::AF_INET), IpAddrBytes::V4(_)) => prefix_len <= 32,
+        (Ok(CSocketAddrFamily::AF_INET6), IpAddrBytes::V6(_)) => prefix_len <= 128,
+        _ => false,
+    };
+    if !is_valid {
+        return_errno_with_message!(Errno::EINVAL, "the allowed IP is invalid");
+    }
+
+    Ok(IpCidr::new(IpAddress::from(addr), prefix_len))
+}
+
+fn device_to_segment(
+    request_header: &CMsgSegHdr,
+    iface: &Arc<Iface>,
+    wireguard: &WireGuard,
+) -> WgSegment {
+    let header = new_response_header(request_header, GENL_ID_WIREGUARD);
+    let body = new_response_body(WgCmd::GET_DEVICE as u8, WG_GENL_VERSION);
+
+    let status = wireguard.status();
+
+    let mut attrs = vec![
+        WgDeviceAttr::ListenPort(status.listen_port),
+        WgDeviceAttr::Fwmark(status.fwmark),
+        WgDeviceAttr::IfIndex(iface.index()),
+        WgDeviceAttr::IfName(CString::new(iface.name()).unwrap()),
+    ];
+    if let Some(private_key) = status.private_key {
+        attrs.push(WgDeviceAttr::PrivateKey(private_key));
+    }
+    if let Some(public_key) = status.public_key {
+        attrs.push(WgDeviceAttr::PublicKey(public_key));
+    }
+
+    if !status.peers.is_empty() {
+        let peers: Vec<ArrayElem> = status.peers.iter().map(peer_to_elem).collect();
+        attrs.push(WgDeviceAttr::Peers(Nested::new(&peers)));
+    }
+
+    WgSegment::new(header, body, attrs)
+}
+
+fn peer_to_elem(peer: &WireGuardPeerStatus) -> ArrayElem {
+    let last_handshake_time = peer
+        .last_handshake_time
+        .map(timespec_t::from)
+        .unwrap_or_default();
+
+    let mut attrs = vec![
+        WgPeerAttr::PublicKey(peer.public_key),
+        WgPeerAttr::PresharedKey(peer.preshared_key),
+        WgPeerAttr::LastHandshakeTime(last_handshake_time),
+        WgPeerAttr::PersistentKeepaliveInterval(peer.persistent_keepalive_interval),
+        WgPeerAttr::TxBytes(peer.tx_bytes),
+        WgPeerAttr::RxBytes(peer.rx_bytes),
+        WgPeerAttr::ProtocolVersion(PROTOCOL_VERSION),
+    ];
+    if let Some(endpoint) = peer.endpoint {
+        let endpoint = socket_addr_to_c_bytes(&SocketAddr::from(endpoint));
+        attrs.push(WgPeerAttr::Endpoint(endpoint));
+    }
+
+    let allowed_ips: Vec<ArrayElem> = peer
+        .allowed_ips
+        .iter()
+        .map(|cidr| {
+            let family = match cidr.address() {
+                IpAddress::Ipv4(_) => CSocketAddrFamily::AF_INET,
+                IpAddress::Ipv6(_) => CSocketAddrFamily::AF_INET6,
+            };
+            let attrs = [
+                WgAllowedIpAttr::Family(family as u16),
+                WgAllowedIpAttr::IpAddr(IpAddrBytes::from(cidr.address())),
+                WgAllowedIpAttr::CidrMask(cidr.prefix_len()),
+            ];
+            ArrayElem(Nested::new(&attrs))
+        })
+        .collect();
+    attrs.push(WgPeerAttr::AllowedIps(Nested::new(&allowed_ips)));
+
+    ArrayElem(Nested::new(&attrs))
+}
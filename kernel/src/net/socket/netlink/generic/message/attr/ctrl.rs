@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Controller-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/genetlink.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum CtrlAttrClass {
+    UNSPEC = 0,
+    FAMILY_ID = 1,
+    FAMILY_NAME = 2,
+    VERSION = 3,
+    HDRSIZE = 4,
+    MAXATTR = 5,
+    OPS = 6,
+    MCAST_GROUPS = 7,
+    POLICY = 8,
+    OP_POLICY = 9,
+    OP = 10,
+}
+
+#[derive(Debug)]
+pub enum CtrlAttr {
+    FamilyId(u16),
+    FamilyName(CString),
+    Version(u32),
+    /// The length of the family-specific header after the generic netlink header.
+    HdrSize(u32),
+    MaxAttr(u32),
+}
+
+/// The size limit for family names.
+const GENL_NAMSIZ: usize = 16;
+
+impl CtrlAttr {
+    fn class(&self) -> CtrlAttrClass {
+        match self {
+            CtrlAttr::FamilyId(_) => CtrlAttrClass::FAMILY_ID,
+            CtrlAttr::FamilyName(_) => CtrlAttrClass::FAMILY_NAME,
+            CtrlAttr::Version(_) => CtrlAttrClass::VERSION,
+            CtrlAttr::HdrSize(_) => CtrlAttrClass::HDRSIZE,
+            CtrlAttr::MaxAttr(_) => CtrlAttrClass::MAXATTR,
+        }
+    }
+}
+
+impl Attribute for CtrlAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            CtrlAttr::FamilyId(id) => id.as_bytes(),
+            CtrlAttr::FamilyName(name) => name.as_bytes_with_nul(),
+            CtrlAttr::Version(version) => version.as_bytes(),
+            CtrlAttr::HdrSize(hdr_size) => hdr_size.as_bytes(),
+            CtrlAttr::MaxAttr(max_attr) => max_attr.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match CtrlAttrClass::try_from(header.type_()) {
+            Ok(CtrlAttrClass::FAMILY_ID) => Self::FamilyId(reader.read_val()?),
+            Ok(CtrlAttrClass::FAMILY_NAME) => {
+                Self::FamilyName(reader.read_cstring_with_max_len(GENL_NAMSIZ)?)
+            }
+            class => {
+                debug!("controller attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::wire::{IpAddress, Ipv4Address, Ipv6Address};
+
+use super::Nested;
+use crate::{
+    net::{
+        iface::WG_KEY_LEN,
+        socket::netlink::message::{Attribute, CAttrHeader},
+    },
+    prelude::*,
+    util::MultiRead,
+};
+
+pub mod ctrl;
+pub mod wireguard;
+
+/// The size limit for interface names.
+const IFNAME_SIZE: usize = 16;
+
+/// A key of WireGuard in an attribute payload.
+pub type Key = [u8; WG_KEY_LEN];
+
+/// Reads the payload of an attribute as a key.
+///
+/// Like Linux, the payload must be exactly as long as a key.
+fn read_key(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Key> {
+    if header.payload_len() != WG_KEY_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the key length is invalid");
+    }
+
+    reader.read_val()
+}
+
+/// Reads the payload of an attribute as bytes.
+fn read_bytes(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; header.payload_len()];
+    reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+    Ok(bytes)
+}
+
+/// An element of a nested array (e.g., a peer in the peer list of a WireGuard device).
+///
+/// Like Linux, the types of the elements are ignored when they are read, and they are written
+/// as zeros.
+#[derive(Debug)]
+pub struct ArrayElem(pub Nested);
+
+impl Attribute for ArrayElem {
+    fn type_(&self) -> u16 {
+        0
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(Some(Self(Nested::read_from(header, reader)?)))
+    }
+}
+
+/// An IPv4 or IPv6 address in an attribute payload.
+///
+/// The address family is determined by the payload length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpAddrBytes {
+    V4([u8; 4]),
+    V6([u8; 16]),
+}
+
+impl IpAddrBytes {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::V4(bytes) => bytes,
+            Self::V6(bytes) => bytes,
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Self> {
+        match header.payload_len() {
+            4 => Ok(Self::V4(reader.read_val()?)),
+            16 => Ok(Self::V6(reader.read_val()?)),
+            _ => return_errno_with_message!(Errno::EINVAL, "the address length is invalid"),
+        }
+    }
+}
+
+impl From<IpAddress> for IpAddrBytes {
+    fn from(value: IpAddress) -> Self {
+        match value {
+            IpAddress::Ipv4(ipv4_addr) => Self::V4(ipv4_addr.octets()),
+            IpAddress::Ipv6(ipv6_addr) => Self::V6(ipv6_addr.octets()),
+        }
+    }
+}
+
+impl From<IpAddrBytes> for IpAddress {
+    fn from(value: IpAddrBytes) -> Self {
+        match value {
+            IpAddrBytes::V4(bytes) => IpAddress::Ipv4(Ipv4Address::from(bytes)),
+            IpAddrBytes::V6(bytes) => IpAddress::Ipv6(Ipv6Address::from(bytes)),
+        }
+    }
+}
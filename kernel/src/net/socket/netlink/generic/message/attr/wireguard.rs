@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{read_bytes, read_key, IpAddrBytes, Key, Nested, IFNAME_SIZE};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    time::timespec_t,
+    util::MultiRead,
+};
+
+/// Device-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum WgDeviceAttrClass {
+    UNSPEC = 0,
+    IFINDEX = 1,
+    IFNAME = 2,
+    PRIVATE_KEY = 3,
+    PUBLIC_KEY = 4,
+    FLAGS = 5,
+    LISTEN_PORT = 6,
+    FWMARK = 7,
+    PEERS = 8,
+}
+
+#[derive(Debug)]
+pub enum WgDeviceAttr {
+    IfIndex(u32),
+    IfName(CString),
+    PrivateKey(Key),
+    PublicKey(Key),
+    Flags(u32),
+    ListenPort(u16),
+    Fwmark(u32),
+    /// The peers, each of which is an [`ArrayElem`] that contains [`WgPeerAttr`]s.
+    ///
+    /// [`ArrayElem`]: super::ArrayElem
+    Peers(Nested),
+}
+
+bitflags! {
+    /// Device flags.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+    pub struct WgDeviceFlags: u32 {
+        const REPLACE_PEERS = 1 << 0;
+    }
+}
+
+impl WgDeviceAttr {
+    fn class(&self) -> WgDeviceAttrClass {
+        match self {
+            WgDeviceAttr::IfIndex(_) => WgDeviceAttrClass::IFINDEX,
+            WgDeviceAttr::IfName(_) => WgDeviceAttrClass::IFNAME,
+            WgDeviceAttr::PrivateKey(_) => WgDeviceAttrClass::PRIVATE_KEY,
+            WgDeviceAttr::PublicKey(_) => WgDeviceAttrClass::PUBLIC_KEY,
+            WgDeviceAttr::Flags(_) => WgDeviceAttrClass::FLAGS,
+            WgDeviceAttr::ListenPort(_) => WgDeviceAttrClass::LISTEN_PORT,
+            WgDeviceAttr::Fwmark(_) => WgDeviceAttrClass::FWMARK,
+            WgDeviceAttr::Peers(_) => WgDeviceAttrClass::PEERS,
+        }
+    }
+}
+
+impl Attribute for WgDeviceAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            WgDeviceAttr::IfIndex(index) => index.as_bytes(),
+            WgDeviceAttr::IfName(name) => name.as_bytes_with_nul(),
+            WgDeviceAttr::PrivateKey(key) | WgDeviceAttr::PublicKey(key) => key,
+            WgDeviceAttr::Flags(flags) => flags.as_bytes(),
+            WgDeviceAttr::ListenPort(port) => port.as_bytes(),
+            WgDeviceAttr::Fwmark(fwmark) => fwmark.as_bytes(),
+            WgDeviceAttr::Peers(peers) => peers.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match WgDeviceAttrClass::try_from(header.type_()) {
+            Ok(WgDeviceAttrClass::IFINDEX) => Self::IfIndex(reader.read_val()?),
+            Ok(WgDeviceAttrClass::IFNAME) => {
+                Self::IfName(reader.read_cstring_with_max_len(IFNAME_SIZE)?)
+            }
+            Ok(WgDeviceAttrClass::PRIVATE_KEY) => Self::PrivateKey(read_key(header, reader)?),
+            Ok(WgDeviceAttrClass::FLAGS) => Self::Flags(reader.read_val()?),
+            Ok(WgDeviceAttrClass::LISTEN_PORT) => Self::ListenPort(reader.read_val()?),
+            Ok(WgDeviceAttrClass::FWMARK) => Self::Fwmark(reader.read_val()?),
+            Ok(WgDeviceAttrClass::PEERS) => Self::Peers(Nested::read_from(header, reader)?),
+            class => {
+                debug!("WireGuard device attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Peer-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum WgPeerAttrClass {
+    UNSPEC = 0,
+    PUBLIC_KEY = 1,
+    PRESHARED_KEY = 2,
+    FLAGS = 3,
+    ENDPOINT = 4,
+    PERSISTENT_KEEPALIVE_INTERVAL = 5,
+    LAST_HANDSHAKE_TIME = 6,
+    RX_BYTES = 7,
+    TX_BYTES = 8,
+    ALLOWEDIPS = 9,
+    PROTOCOL_VERSION = 10,
+}
+
+#[derive(Debug)]
+pub enum WgPeerAttr {
+    PublicKey(Key),
+    PresharedKey(Key),
+    Flags(u32),
+    /// The endpoint, which is a `sockaddr_in` or a `sockaddr_in6`.
+    Endpoint(Vec<u8>),
+    PersistentKeepaliveInterval(u16),
+    LastHandshakeTime(timespec_t),
+    RxBytes(u64),
+    TxBytes(u64),
+    /// The allowed IPs, each of which is an [`ArrayElem`] that contains [`WgAllowedIpAttr`]s.
+    ///
+    /// [`ArrayElem`]: super::ArrayElem
+    AllowedIps(Nested),
+    ProtocolVersion(u32),
+}
+
+bitflags! {
+    /// Peer flags.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+    pub struct WgPeerFlags: u32 {
+        const REMOVE_ME = 1 << 0;
+        const REPLACE_ALLOWEDIPS = 1 << 1;
+        const UPDATE_ONLY = 1 << 2;
+    }
+}
+
+impl WgPeerAttr {
+    fn class(&self) -> WgPeerAttrClass {
+        match self {
+            WgPeerAttr::PublicKey(_) => WgPeerAttrClass::PUBLIC_KEY,
+            WgPeerAttr::PresharedKey(_) => WgPeerAttrClass::PRESHARED_KEY,
+            WgPeerAttr::Flags(_) => WgPeerAttrClass::FLAGS,
+            WgPeerAttr::Endpoint(_) => WgPeerAttrClass::ENDPOINT,
+            WgPeerAttr::PersistentKeepaliveInterval(_) => {
+                WgPeerAttrClass::PERSISTENT_KEEPALIVE_INTERVAL
+            }
+            WgPeerAttr::LastHandshakeTime(_) => WgPeerAttrClass::LAST_HANDSHAKE_TIME,
+            WgPeerAttr::RxBytes(_) => WgPeerAttrClass::RX_BYTES,
+            WgPeerAttr::TxBytes(_) => WgPeerAttrClass::TX_BYTES,
+            WgPeerAttr::AllowedIps(_) => WgPeerAttrClass::ALLOWEDIPS,
+            WgPeerAttr::ProtocolVersion(_) => WgPeerAttrClass::PROTOCOL_VERSION,
+        }
+    }
+}
+
+impl Attribute for WgPeerAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            WgPeerAttr::PublicKey(key) | WgPeerAttr::PresharedKey(key) => key,
+            WgPeerAttr::Flags(flags) => flags.as_bytes(),
+            WgPeerAttr::Endpoint(endpoint) => endpoint,
+            WgPeerAttr::PersistentKeepaliveInterval(interval) => interval.as_bytes(),
+            WgPeerAttr::LastHandshakeTime(time) => time.as_bytes(),
+            WgPeerAttr::RxBytes(bytes) | WgPeerAttr::TxBytes(bytes) => bytes.as_bytes(),
+            WgPeerAttr::AllowedIps(allowed_ips) => allowed_ips.as_bytes(),
+            WgPeerAttr::ProtocolVersion(version) => version.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match WgPeerAttrClass::try_from(header.type_()) {
+            Ok(WgPeerAttrClass::PUBLIC_KEY) => Self::PublicKey(read_key(header, reader)?),
+            Ok(WgPeerAttrClass::PRESHARED_KEY) => Self::PresharedKey(read_key(header, reader)?),
+            Ok(WgPeerAttrClass::FLAGS) => Self::Flags(reader.read_val()?),
+            Ok(WgPeerAttrClass::ENDPOINT) => Self::Endpoint(read_bytes(header, reader)?),
+            Ok(WgPeerAttrClass::PERSISTENT_KEEPALIVE_INTERVAL) => {
+                Self::PersistentKeepaliveInterval(reader.read_val()?)
+            }
+            Ok(WgPeerAttrClass::ALLOWEDIPS) => Self::AllowedIps(Nested::read_from(header, reader)?),
+            Ok(WgPeerAttrClass::PROTOCOL_VERSION) => Self::ProtocolVersion(reader.read_val()?),
+            class => {
+                debug!("WireGuard peer attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Allowed-IP-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum WgAllowedIpAttrClass {
+    UNSPEC = 0,
+    FAMILY = 1,
+    IPADDR = 2,
+    CIDR_MASK = 3,
+}
+
+#[derive(Debug)]
+pub enum WgAllowedIpAttr {
+    /// The address family, which is `AF_INET` or `AF_INET6`.
+    Family(u16),
+    IpAddr(IpAddrBytes),
+    CidrMask(u8),
+}
+
+impl WgAllowedIpAttr {
+    fn class(&self) -> WgAllowedIpAttrClass {
+        match self {
+            WgAllowedIpAttr::Family(_) => WgAllowedIpAttrClass::FAMILY,
+            WgAllowedIpAttr::IpAddr(_) => WgAllowedIpAttrClass::IPADDR,
+            WgAllowedIpAttr::CidrMask(_) => WgAllowedIpAttrClass::CIDR_MASK,
+        }
+    }
+}
+
+impl Attribute for WgAllowedIpAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            WgAllowedIpAttr::Family(family) => family.as_bytes(),
+            WgAllowedIpAttr::IpAddr(addr) => addr.as_bytes(),
+            WgAllowedIpAttr::CidrMask(mask) => mask.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match WgAllowedIpAttrClass::try_from(header.type_()) {
+            Ok(WgAllowedIpAttrClass::FAMILY) => Self::Family(reader.read_val()?),
+            Ok(WgAllowedIpAttrClass::IPADDR) => {
+                Self::IpAddr(IpAddrBytes::read_from(header, reader)?)
+            }
+            Ok(WgAllowedIpAttrClass::CIDR_MASK) => Self::CidrMask(reader.read_val()?),
+            class => {
+                debug!("WireGuard allowed IP attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink message types for the generic netlink protocol.
+//!
+//! This module defines how to interpret messages sent from user space and how to write
+//! kernel messages back to user space.
+
+mod attr;
+mod segment;
+
+pub(super) use attr::{
+    ctrl::CtrlAttr,
+    wireguard::{WgAllowedIpAttr, WgDeviceAttr, WgDeviceFlags, WgPeerAttr, WgPeerFlags},
+    ArrayElem, IpAddrBytes,
+};
+pub(super) use segment::{
+    CtrlCmd, CtrlSegment, GenlMsgBody, GenlSegment, WgCmd, WgSegment, GENL_ID_CTRL,
+    GENL_ID_WIREGUARD,
+};
+
+use crate::net::socket::netlink::message::Message;
+pub(super) use crate::net::socket::netlink::message::Nested;
+
+/// A generic netlink message.
+pub(super) type GenlMessage = Message<GenlSegment>;
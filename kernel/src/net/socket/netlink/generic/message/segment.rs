@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines the segments of the generic netlink protocol.
+//!
+//! Each segment type is the ID of a family, and each segment body is a [`CGenlMsgHdr`], whose
+//! command is interpreted by the family. Only the `nlctrl` family and the `wireguard` family are
+//! supported.
+
+use align_ext::AlignExt;
+
+use super::attr::{ctrl::CtrlAttr, wireguard::WgDeviceAttr};
+use crate::{
+    net::socket::netlink::message::{
+        CMsgSegHdr, DoneSegment, ErrorSegment, ProtocolSegment, SegmentBody, SegmentCommon,
+        NLMSG_ALIGN,
+    },
+    prelude::*,
+    util::{MultiRead, MultiWrite},
+};
+
+pub type CtrlSegment = SegmentCommon<GenlMsgBody, CtrlAttr>;
+pub type WgSegment = SegmentCommon<GenlMsgBody, WgDeviceAttr>;
+
+/// The generic netlink segment, which is the basic unit of a generic netlink message.
+#[derive(Debug)]
+pub enum GenlSegment {
+    Ctrl(CtrlSegment),
+    WireGuard(WgSegment),
+    /// A segment whose family is not supported.
+    ///
+    /// The segment is skipped, but it is still answered with an error.
+    Unsupported(CMsgSegHdr),
+    Done(DoneSegment),
+    Error(ErrorSegment),
+}
+
+/// `genlmsghdr` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/genetlink.h>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CGenlMsgHdr {
+    pub cmd: u8,
+    pub version: u8,
+    pub reserved: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GenlMsgBody {
+    /// The command, which is one of [`CtrlCmd`] or [`WgCmd`], depending on the family.
+    pub cmd: u8,
+    pub version: u8,
+}
+
+impl SegmentBody for GenlMsgBody {
+    type CType = CGenlMsgHdr;
+}
+
+impl TryFrom<CGenlMsgHdr> for GenlMsgBody {
+    type Error = Error;
+
+    fn try_from(value: CGenlMsgHdr) -> Result<Self> {
+        // Like Linux, the version is not checked.
+        Ok(Self {
+            cmd: value.cmd,
+            version: value.version,
+        })
+    }
+}
+
+impl From<GenlMsgBody> for CGenlMsgHdr {
+    fn from(value: GenlMsgBody) -> Self {
+        Self {
+            cmd: value.cmd,
+            version: value.version,
+            reserved: 0,
+        }
+    }
+}
+
+/// The fixed ID of the `nlctrl` family.
+pub const GENL_ID_CTRL: u16 = 0x10;
+
+/// The ID of the `wireguard` family.
+///
+/// Linux allocates the IDs of the other families dynamically, starting from `GENL_START_ALLOC`.
+/// Since the families are resolved by their names, we use the first allocatable ID.
+pub const GENL_ID_WIREGUARD: u16 = 0x13;
+
+/// The commands of the `nlctrl` family.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/genetlink.h>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub enum CtrlCmd {
+    NEWFAMILY = 1,
+    GETFAMILY = 3,
+}
+
+/// The commands of the `wireguard` family.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/wireguard.h>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub enum WgCmd {
+    GET_DEVICE = 0,
+    SET_DEVICE = 1,
+}
+
+impl ProtocolSegment for GenlSegment {
+    fn header(&self) -> &CMsgSegHdr {
+        match self {
+            GenlSegment::Ctrl(ctrl_segment) => ctrl_segment.header(),
+            GenlSegment::WireGuard(wg_segment) => wg_segment.header(),
+            GenlSegment::Unsupported(header) => header,
+            GenlSegment::Done(done_segment) => done_segment.header(),
+            GenlSegment::Error(error_segment) => error_segment.header(),
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut CMsgSegHdr {
+        match self {
+            GenlSegment::Ctrl(ctrl_segment) => ctrl_segment.header_mut(),
+            GenlSegment::WireGuard(wg_segment) => wg_segment.header_mut(),
+            GenlSegment::Unsupported(header) => header,
+            GenlSegment::Done(done_segment) => done_segment.header_mut(),
+            GenlSegment::Error(error_segment) => error_segment.header_mut(),
+        }
+    }
+
+    fn read_from(reader: &mut dyn MultiRead) -> Result<Self> {
+        let header = reader.read_val::<CMsgSegHdr>()?;
+
+        let segment = match header.type_ {
+            GENL_ID_CTRL => GenlSegment::Ctrl(CtrlSegment::read_from(header, reader)?),
+            GENL_ID_WIREGUARD => GenlSegment::WireGuard(WgSegment::read_from(header, reader)?),
+            _ => {
+                // Skip the rest of the segment, including the padding bytes.
+                let skipped_len = (header.len as usize)
+                    .checked_sub(size_of::<CMsgSegHdr>())
+                    .ok_or_else(|| {
+                        Error::with_message(Errno::EINVAL, "the message length is too small")
+                    })?
+                    .align_up(NLMSG_ALIGN)
+                    .min(reader.sum_lens());
+                reader.skip(skipped_len);
+                GenlSegment::Unsupported(header)
+            }
+        };
+
+        Ok(segment)
+    }
+
+    fn write_to(&self, writer: &mut dyn MultiWrite) -> Result<()> {
+        match self {
+            GenlSegment::Ctrl(ctrl_segment) => ctrl_segment.write_to(writer)?,
+            GenlSegment::WireGuard(wg_segment) => wg_segment.write_to(writer)?,
+            GenlSegment::Done(done_segment) => done_segment.write_to(writer)?,
+            GenlSegment::Error(error_segment) => error_segment.write_to(writer)?,
+            GenlSegment::Unsupported(_) => {
+                unreachable!("kernel should not write unsupported segments to user space");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::bound::BoundNetlinkGeneric;
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        netlink::{table::NETLINK_SOCKET_TABLE, NetlinkSocketAddr, StandardNetlinkProtocol},
+        util::datagram_common,
+    },
+    prelude::*,
+    process::signal::Pollee,
+};
+
+pub(super) struct UnboundNetlinkGeneric {
+    _private: (),
+}
+
+impl UnboundNetlinkGeneric {
+    pub(super) const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl datagram_common::Unbound for UnboundNetlinkGeneric {
+    type Endpoint = NetlinkSocketAddr;
+    type BindOptions = ();
+
+    type Bound = BoundNetlinkGeneric;
+
+    fn bind(
+        &mut self,
+        endpoint: &Self::Endpoint,
+        _pollee: &Pollee,
+        _options: Self::BindOptions,
+    ) -> Result<BoundNetlinkGeneric> {
+        let bound_handle =
+            NETLINK_SOCKET_TABLE.bind(StandardNetlinkProtocol::GENERIC as _, endpoint)?;
+
+        Ok(BoundNetlinkGeneric::new(bound_handle))
+    }
+
+    fn bind_ephemeral(
+        &mut self,
+        _remote_endpoint: &Self::Endpoint,
+        _pollee: &Pollee,
+    ) -> Result<Self::Bound> {
+        let bound_handle = NETLINK_SOCKET_TABLE.bind(
+            StandardNetlinkProtocol::GENERIC as _,
+            &NetlinkSocketAddr::new_unspecified(),
+        )?;
+
+        Ok(BoundNetlinkGeneric::new(bound_handle))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        IoEvents::OUT
+    }
+}
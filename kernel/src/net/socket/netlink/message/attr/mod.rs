@@ -54,6 +54,11 @@ impl CAttrHeader {
     pub fn type_(&self) -> u16 {
         self.type_ & ATTRIBUTE_TYPE_MASK
     }
+
+    /// Returns the payload length (excluding padding).
+    pub fn payload_len(&self) -> usize {
+        (self.len as usize).saturating_sub(size_of::<Self>())
+    }
 }
 
 const IS_NESTED_MASK: u16 = 1u16 << 15;
@@ -88,14 +93,20 @@ pub trait Attribute: Debug + Send + Sync {
         self.total_len_with_padding() - self.total_len()
     }
 
-    /// Reads the attribute from the `reader`.
-    fn read_from(reader: &mut dyn MultiRead) -> Result<Self>
+    /// Reads the attribute payload from the `reader`.
+    ///
+    /// The attribute header has already been read. This method returns `None` if the attribute
+    /// is unknown, in which case the payload will be skipped.
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
     where
         Self: Sized;
 
     /// Reads all attributes from the reader.
     ///
-    /// The cumulative length of the read attributes must not exceed total_len.
+    /// The cumulative length of the read attributes must not exceed total_len. Like Linux,
+    /// unknown attributes are ignored.
+    ///
+    /// Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html#unknown-attributes>.
     fn read_all_from(reader: &mut dyn MultiRead, mut total_len: usize) -> Result<Vec<Self>>
     where
         Self: Sized,
@@ -103,14 +114,33 @@ pub trait Attribute: Debug + Send + Sync {
         let mut res = Vec::new();
 
         while total_len > 0 {
-            let attr = Self::read_from(reader)?;
-            total_len -= attr.total_len();
-
-            let padding_len = attr.padding_len().min(total_len);
+            if total_len < size_of::<CAttrHeader>() {
+                return_errno_with_message!(Errno::EINVAL, "the attribute length is too small");
+            }
+            let header = reader.read_val::<CAttrHeader>()?;
+
+            let attr_len = header.len as usize;
+            if attr_len < size_of::<CAttrHeader>() || attr_len > total_len {
+                return_errno_with_message!(Errno::EINVAL, "the attribute length is invalid");
+            }
+            total_len -= attr_len;
+
+            let payload_len = header.payload_len();
+            let remain_len = reader.sum_lens();
+            if let Some(attr) = Self::read_from(&header, reader)? {
+                res.push(attr);
+            }
+            // The payload may not be stored verbatim in the attribute (e.g., a string that lacks
+            // the nul terminator), so count the bytes actually consumed from the reader.
+            let read_len = remain_len - reader.sum_lens();
+            if read_len > payload_len {
+                return_errno_with_message!(Errno::EINVAL, "the attribute payload is too small");
+            }
+            reader.skip(payload_len - read_len);
+
+            let padding_len = (attr_len.align_up(NLMSG_ALIGN) - attr_len).min(total_len);
             reader.skip(padding_len);
             total_len -= padding_len;
-
-            res.push(attr);
         }
 
         Ok(res)
@@ -132,3 +162,40 @@ pub trait Attribute: Debug + Send + Sync {
         Ok(())
     }
 }
+
+/// The payload of an attribute that contains nested attributes.
+///
+/// The nested attributes are parsed on demand, since their types may depend on other attributes
+/// (e.g., the data of an expression depends on the expression name).
+#[derive(Debug, Clone)]
+pub struct Nested(Vec<u8>);
+
+impl Nested {
+    pub fn new<A: Attribute>(attrs: &[A]) -> Self {
+        let len = attrs.iter().map(|attr| attr.total_len_with_padding()).sum();
+        let mut bytes = vec![0u8; len];
+
+        let mut writer = VmWriter::from(bytes.as_mut_slice()).to_fallible();
+        for attr in attrs.iter() {
+            // The buffer is large enough to hold the attributes.
+            attr.write_to(&mut writer).unwrap();
+        }
+
+        Self(bytes)
+    }
+
+    pub fn parse<A: Attribute>(&self) -> Result<Vec<A>> {
+        let mut reader = VmReader::from(self.0.as_slice()).to_fallible();
+        A::read_all_from(&mut reader, self.0.len())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Self> {
+        let mut bytes = vec![0u8; header.payload_len()];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+        Ok(Self(bytes))
+    }
+}
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::Attribute;
+use super::{Attribute, CAttrHeader};
 use crate::{prelude::*, util::MultiRead};
 
 /// A special type indicates that a segment cannot have attributes.
@@ -16,7 +16,7 @@ impl Attribute for NoAttr {
         match *self {}
     }
 
-    fn read_from(_reader: &mut dyn MultiRead) -> Result<Self>
+    fn read_from(_header: &CAttrHeader, _reader: &mut dyn MultiRead) -> Result<Option<Self>>
     where
         Self: Sized,
     {
@@ -8,11 +8,11 @@
 mod attr;
 mod segment;
 
-pub(super) use attr::{noattr::NoAttr, Attribute, CAttrHeader};
+pub(super) use attr::{noattr::NoAttr, Attribute, CAttrHeader, Nested};
 pub(super) use segment::{
     ack::{DoneSegment, ErrorSegment},
     common::SegmentCommon,
-    header::{CMsgSegHdr, GetRequestFlags, SegHdrCommonFlags},
+    header::{CMsgSegHdr, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags},
     CSegmentType, SegmentBody,
 };
 
@@ -44,11 +44,16 @@ impl<T: ProtocolSegment> Message<T> {
     }
 
     pub(super) fn read_from(reader: &mut dyn MultiRead) -> Result<Self> {
-        // FIXME: Does a request contain only one segment? We need to investigate further.
-        let segments = {
-            let segment = T::read_from(reader)?;
-            vec![segment]
-        };
+        // A request can contain multiple segments (e.g., the segments in a batch). Like Linux,
+        // the remaining bytes are ignored if they are too short to hold a segment header.
+        // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netlink/af_netlink.c>.
+        let mut segments = Vec::new();
+        while reader.sum_lens() >= size_of::<CMsgSegHdr>() {
+            segments.push(T::read_from(reader)?);
+        }
+        if segments.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "the message contains no segments");
+        }
 
         Ok(Self { segments })
     }
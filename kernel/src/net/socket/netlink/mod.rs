@@ -38,14 +38,23 @@
 //!
 
 mod addr;
+mod connector;
+mod generic;
 mod message;
+mod netfilter;
 mod route;
 mod table;
+mod uevent;
 
 pub use addr::{GroupIdSet, NetlinkSocketAddr};
+pub use connector::{proc_events, NetlinkConnectorSocket};
+pub use generic::NetlinkGenericSocket;
+pub use netfilter::NetlinkNetfilterSocket;
 pub use route::NetlinkRouteSocket;
 pub use table::{is_valid_protocol, StandardNetlinkProtocol};
+pub use uevent::NetlinkUeventSocket;
 
 pub(in crate::net) fn init() {
     table::init();
+    uevent::init();
 }
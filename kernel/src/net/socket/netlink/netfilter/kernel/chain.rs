@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle chain-related requests.
+
+use aster_bigtcp::iface::FilterHook;
+
+use super::util::{
+    finish_response, is_dump_request, name_to_str, new_response_body, new_response_header,
+    parse_family, parse_specific_family, str_to_name,
+};
+use crate::{
+    net::{
+        netfilter::{self, BaseChain, Chain, ChainType, Table, Transaction, Verdict},
+        socket::netlink::{
+            message::{CMsgSegHdr, NewRequestFlags},
+            netfilter::message::{
+                Be32, Be64, ChainAttr, ChainSegment, HookAttr, Nested, NfnlSegment, NftMsgType,
+            },
+        },
+    },
+    prelude::*,
+};
+
+pub(super) fn do_new_chain(tx: &mut Transaction, request_segment: &ChainSegment) -> Result<()> {
+    let family = parse_specific_family(request_segment.body().family)?;
+    let request = ChainRequest::parse(request_segment)?;
+
+    let Some(table_name) = request.table else {
+        return_errno_with_message!(Errno::EINVAL, "the table name is not specified");
+    };
+    let Some(name) = request.name else {
+        return_errno_with_message!(Errno::EINVAL, "the chain name is not specified");
+    };
+    let type_ = request.type_.map(parse_type).transpose()?;
+    let hook = request.hook.map(parse_hook).transpose()?;
+    let policy = request.policy.map(parse_policy).transpose()?;
+    if hook.is_none() && policy.is_some() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only base chains have policies");
+    }
+
+    let table = tx.table_mut(family, table_name)?;
+
+    let Ok(chain) = table.chain_mut(name) else {
+        let base = hook.map(|(hook, priority)| BaseChain {
+            type_: type_.unwrap_or(ChainType::Filter),
+            hook,
+            priority,
+            policy: policy.unwrap_or(Verdict::Accept),
+        });
+        return table.add_chain(name.to_string(), base);
+    };
+
+    let request_flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    if request_flags.contains(NewRequestFlags::EXCL) {
+        return_errno_with_message!(Errno::EEXIST, "the chain already exists");
+    }
+    if let Some((hook, priority)) = hook {
+        if chain
+            .base()
+            .is_none_or(|base| base.hook != hook || base.priority != priority)
+        {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the chain hook cannot be changed");
+        }
+    }
+    if let Some(type_) = type_ {
+        if chain.base().is_some_and(|base| base.type_ != type_) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the chain type cannot be changed");
+        }
+    }
+    if let Some(policy) = policy {
+        chain.set_policy(policy)?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_del_chain(tx: &mut Transaction, request_segment: &ChainSegment) -> Result<()> {
+    let family = parse_specific_family(request_segment.body().family)?;
+    let request = ChainRequest::parse(request_segment)?;
+
+    let Some(table_name) = request.table else {
+        return_errno_with_message!(Errno::EINVAL, "the table name is not specified");
+    };
+    let table = tx.table_mut(family, table_name)?;
+
+    let name = match (request.name, request.handle) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(handle)) => table
+            .chains()
+            .iter()
+            .find(|chain| chain.handle() == handle)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the chain does not exist"))?
+            .name()
+            .to_string(),
+        (None, None) => {
+            return_errno_with_message!(Errno::EINVAL, "the chain is not specified")
+        }
+    };
+
+    table.remove_chain(&name)
+}
+
+pub(super) fn do_get_chain(request_segment: &ChainSegment) -> Result<Vec<NfnlSegment>> {
+    let family = parse_family(request_segment.body().family)?;
+    let request = ChainRequest::parse(request_segment)?;
+    let request_header = request_segment.header();
+    let dump_all = is_dump_request(request_header);
+
+    let mut response_segments: Vec<NfnlSegment> = netfilter::with_ruleset(|ruleset| {
+        if dump_all {
+            return Ok(ruleset
+                .tables()
+                .iter()
+                .filter(|table| family.is_none_or(|family| table.family() == family))
+                .filter(|table| request.table.is_none_or(|name| table.name() == name))
+                .flat_map(|table| {
+                    table
+                        .chains()
+                        .iter()
+                        .map(|chain| chain_to_new_chain(request_header, table, chain))
+                })
+                .collect());
+        }
+
+        let (Some(family), Some(table_name), Some(name)) = (family, request.table, request.name)
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the chain is not specified");
+        };
+        let table = ruleset.table(family, table_name)?;
+        let chain = table.chain(name)?;
+
+        Ok(vec![chain_to_new_chain(request_header, table, chain)])
+    })?;
+
+    finish_response(request_header, dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+/// The attributes of a chain-related request.
+struct ChainRequest<'a> {
+    table: Option<&'a str>,
+    name: Option<&'a str>,
+    handle: Option<u64>,
+    hook: Option<&'a Nested>,
+    policy: Option<u32>,
+    type_: Option<&'a CStr>,
+}
+
+impl<'a> ChainRequest<'a> {
+    fn parse(request_segment: &'a ChainSegment) -> Result<Self> {
+        let mut request = Self {
+            table: None,
+            name: None,
+            handle: None,
+            hook: None,
+            policy: None,
+            type_: None,
+        };
+
+        for attr in request_segment.attrs() {
+            match attr {
+                ChainAttr::Table(table) => request.table = Some(name_to_str(table)?),
+                ChainAttr::Name(name) => request.name = Some(name_to_str(name)?),
+                ChainAttr::Handle(handle) => request.handle = Some(handle.get()),
+                ChainAttr::Hook(hook) => request.hook = Some(hook),
+                ChainAttr::Policy(policy) => request.policy = Some(policy.get()),
+                ChainAttr::Type(type_) => request.type_ = Some(type_),
+                ChainAttr::Use(_) => (),
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+/// The names of the chain types.
+///
+/// Other types (i.e., `route`) are not supported.
+const CHAIN_TYPE_FILTER: &[u8] = b"filter";
+const CHAIN_TYPE_NAT: &[u8] = b"nat";
+
+/// The hook numbers (i.e., `NF_INET_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter.h>.
+const HOOKS: [FilterHook; 5] = [
+    FilterHook::PreRouting,
+    FilterHook::Input,
+    FilterHook::Forward,
+    FilterHook::Output,
+    FilterHook::PostRouting,
+];
+
+/// The verdict codes that can be chain policies.
+const NF_DROP: u32 = 0;
+const NF_ACCEPT: u32 = 1;
+
+fn parse_hook(hook: &Nested) -> Result<(FilterHook, i32)> {
+    let mut hook_num = None;
+    let mut priority = None;
+    for attr in hook.parse::<HookAttr>()? {
+        match attr {
+            HookAttr::HookNum(num) => hook_num = Some(num.get()),
+            HookAttr::Priority(prio) => priority = Some(prio.get() as i32),
+        }
+    }
+
+    let (Some(hook_num), Some(priority)) = (hook_num, priority) else {
+        return_errno_with_message!(Errno::EINVAL, "the hook is not specified");
+    };
+    let Some(hook) = HOOKS.get(hook_num as usize) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the hook is not supported");
+    };
+
+    Ok((*hook, priority))
+}
+
+fn parse_type(type_: &CStr) -> Result<ChainType> {
+    match type_.to_bytes() {
+        CHAIN_TYPE_FILTER => Ok(ChainType::Filter),
+        CHAIN_TYPE_NAT => Ok(ChainType::Nat),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the chain type is not supported"),
+    }
+}
+
+fn parse_policy(policy: u32) -> Result<Verdict> {
+    match policy {
+        NF_DROP => Ok(Verdict::Drop),
+        NF_ACCEPT => Ok(Verdict::Accept),
+        _ => return_errno_with_message!(Errno::EINVAL, "the chain policy is invalid"),
+    }
+}
+
+fn chain_to_new_chain(request_header: &CMsgSegHdr, table: &Table, chain: &Chain) -> NfnlSegment {
+    let header = new_response_header(request_header, NftMsgType::NEWCHAIN);
+
+    let mut attrs = vec![
+        ChainAttr::Table(str_to_name(table.name())),
+        ChainAttr::Handle(Be64::new(chain.handle())),
+        ChainAttr::Name(str_to_name(chain.name())),
+    ];
+    if let Some(base) = chain.base() {
+        let hook_num = HOOKS.iter().position(|hook| *hook == base.hook).unwrap();
+        let hook_attrs = [
+            HookAttr::HookNum(Be32::new(hook_num as u32)),
+            HookAttr::Priority(Be32::new(base.priority as u32)),
+        ];
+        let policy = match base.policy {
+            Verdict::Drop => NF_DROP,
+            _ => NF_ACCEPT,
+        };
+        attrs.push(ChainAttr::Hook(Nested::new(&hook_attrs)));
+        attrs.push(ChainAttr::Policy(Be32::new(policy)));
+        let type_ = match base.type_ {
+            ChainType::Filter => CHAIN_TYPE_FILTER,
+            ChainType::Nat => CHAIN_TYPE_NAT,
+        };
+        attrs.push(ChainAttr::Type(CString::new(type_).unwrap()));
+    }
+    attrs.push(ChainAttr::Use(Be32::new(
+        table.num_references(chain.name()) as u32,
+    )));
+
+    NfnlSegment::NewChain(ChainSegment::new(
+        header,
+        new_response_body(table.family()),
+        attrs,
+    ))
+}
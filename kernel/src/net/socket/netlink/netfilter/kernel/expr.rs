@@ -0,0 +1,691 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Convert expressions from and to attributes.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+
+use aster_bigtcp::iface::{IcmpUnreachable, RejectWith};
+
+use super::util::{name_to_str, str_to_name};
+use crate::{
+    net::{
+        netfilter::{
+            CmpOp, Counter, CtKey, Expr, ImmediateData, MetaKey, NatFlags, NatType, NfProto,
+            PayloadBase, Register, Verdict,
+        },
+        socket::netlink::netfilter::message::{
+            Be32, Be64, BitwiseAttr, CmpAttr, CounterAttr, CtAttr, DataAttr, ExprAttr,
+            ImmediateAttr, ListAttr, MasqAttr, MetaAttr, NatAttr, Nested, PayloadAttr, RejectAttr,
+            VerdictAttr,
+        },
+    },
+    prelude::*,
+};
+
+/// Parses the expressions in a rule.
+///
+/// The family of the table is needed to interpret the ICMP codes in `reject` expressions and to
+/// check the families in `nat` expressions.
+pub(super) fn parse_exprs(exprs: &Nested, family: NfProto) -> Result<Vec<Expr>> {
+    exprs
+        .parse::<ListAttr>()?
+        .iter()
+        .map(|ListAttr::Elem(expr)| parse_expr(expr, family))
+        .collect()
+}
+
+/// Converts the expressions in a rule to attributes.
+pub(super) fn exprs_to_attr(exprs: &[Expr]) -> Nested {
+    let elems: Vec<ListAttr> = exprs
+        .iter()
+        .map(|expr| ListAttr::Elem(expr_to_attr(expr)))
+        .collect();
+    Nested::new(&elems)
+}
+
+fn parse_expr(expr: &Nested, family: NfProto) -> Result<Expr> {
+    let mut name = None;
+    let mut data = None;
+    for attr in expr.parse::<ExprAttr>()? {
+        match attr {
+            ExprAttr::Name(expr_name) => name = Some(expr_name),
+            ExprAttr::Data(expr_data) => data = Some(expr_data),
+        }
+    }
+
+    let Some(name) = name else {
+        return_errno_with_message!(Errno::EINVAL, "the expression name is not specified");
+    };
+    let data = data.unwrap_or_else(|| Nested::new::<DataAttr>(&[]));
+
+    match name_to_str(&name)? {
+        EXPR_IMMEDIATE => parse_immediate(&data),
+        EXPR_CMP => parse_cmp(&data),
+        EXPR_BITWISE => parse_bitwise(&data),
+        EXPR_PAYLOAD => parse_payload(&data),
+        EXPR_META => parse_meta(&data),
+        EXPR_CT => parse_ct(&data),
+        EXPR_COUNTER => parse_counter(&data),
+        EXPR_REJECT => parse_reject(&data, family),
+        EXPR_NAT => parse_nat(&data, family),
+        EXPR_MASQ => parse_masq(&data),
+        _ => {
+            debug!("the expression `{:?}` is not supported", name);
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the expression is not supported")
+        }
+    }
+}
+
+const EXPR_IMMEDIATE: &str = "immediate";
+const EXPR_CMP: &str = "cmp";
+const EXPR_BITWISE: &str = "bitwise";
+const EXPR_PAYLOAD: &str = "payload";
+const EXPR_META: &str = "meta";
+const EXPR_CT: &str = "ct";
+const EXPR_COUNTER: &str = "counter";
+const EXPR_REJECT: &str = "reject";
+const EXPR_NAT: &str = "nat";
+const EXPR_MASQ: &str = "masq";
+
+/// The register that holds verdicts (i.e., `NFT_REG_VERDICT`).
+const NFT_REG_VERDICT: u32 = 0;
+
+fn parse_immediate(data: &Nested) -> Result<Expr> {
+    let mut dreg = None;
+    let mut value = None;
+    for attr in data.parse::<ImmediateAttr>()? {
+        match attr {
+            ImmediateAttr::Dreg(reg) => dreg = Some(reg.get()),
+            ImmediateAttr::Data(data) => value = Some(data),
+        }
+    }
+
+    let (Some(dreg), Some(value)) = (dreg, value) else {
+        return_errno_with_message!(Errno::EINVAL, "the immediate data is not specified");
+    };
+
+    let data = if dreg == NFT_REG_VERDICT {
+        ImmediateData::Verdict(parse_verdict(&value)?)
+    } else {
+        let value = parse_value(&value)?;
+        ImmediateData::Value {
+            dreg: Register::new(dreg, value.len())?,
+            value,
+        }
+    };
+
+    Ok(Expr::Immediate(data))
+}
+
+fn parse_cmp(data: &Nested) -> Result<Expr> {
+    let mut sreg = None;
+    let mut op = None;
+    let mut value = None;
+    for attr in data.parse::<CmpAttr>()? {
+        match attr {
+            CmpAttr::Sreg(reg) => sreg = Some(reg.get()),
+            CmpAttr::Op(cmp_op) => op = Some(cmp_op.get()),
+            CmpAttr::Data(data) => value = Some(data),
+        }
+    }
+
+    let (Some(sreg), Some(op), Some(value)) = (sreg, op, value) else {
+        return_errno_with_message!(Errno::EINVAL, "the comparison is not specified");
+    };
+
+    let data = parse_value(&value)?;
+    Ok(Expr::Cmp {
+        sreg: Register::new(sreg, data.len())?,
+        op: CmpOp::try_from(op)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the comparison is invalid"))?,
+        data,
+    })
+}
+
+/// The operation of the bitwise expression that computes `(sreg & mask) ^ xor`.
+///
+/// Other operations (i.e., shifts) are not supported.
+const NFT_BITWISE_BOOL: u32 = 0;
+
+fn parse_bitwise(data: &Nested) -> Result<Expr> {
+    let mut sreg = None;
+    let mut dreg = None;
+    let mut len = None;
+    let mut mask = None;
+    let mut xor = None;
+    let mut op = NFT_BITWISE_BOOL;
+    for attr in data.parse::<BitwiseAttr>()? {
+        match attr {
+            BitwiseAttr::Sreg(reg) => sreg = Some(reg.get()),
+            BitwiseAttr::Dreg(reg) => dreg = Some(reg.get()),
+            BitwiseAttr::Len(bitwise_len) => len = Some(bitwise_len.get() as usize),
+            BitwiseAttr::Mask(data) => mask = Some(data),
+            BitwiseAttr::Xor(data) => xor = Some(data),
+            BitwiseAttr::Op(bitwise_op) => op = bitwise_op.get(),
+        }
+    }
+
+    if op != NFT_BITWISE_BOOL {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the bitwise operation is not supported");
+    }
+    let (Some(sreg), Some(dreg), Some(len), Some(mask), Some(xor)) = (sreg, dreg, len, mask, xor)
+    else {
+        return_errno_with_message!(Errno::EINVAL, "the bitwise operation is not specified");
+    };
+
+    let mask = parse_value(&mask)?;
+    let xor = parse_value(&xor)?;
+    if mask.len() != len || xor.len() != len {
+        return_errno_with_message!(Errno::EINVAL, "the bitwise operands have wrong lengths");
+    }
+
+    Ok(Expr::Bitwise {
+        sreg: Register::new(sreg, len)?,
+        dreg: Register::new(dreg, len)?,
+        mask,
+        xor,
+    })
+}
+
+fn parse_payload(data: &Nested) -> Result<Expr> {
+    let mut dreg = None;
+    let mut base = None;
+    let mut offset = None;
+    let mut len = None;
+    for attr in data.parse::<PayloadAttr>()? {
+        match attr {
+            PayloadAttr::Dreg(reg) => dreg = Some(reg.get()),
+            PayloadAttr::Base(payload_base) => base = Some(payload_base.get()),
+            PayloadAttr::Offset(payload_offset) => offset = Some(payload_offset.get()),
+            PayloadAttr::Len(payload_len) => len = Some(payload_len.get()),
+        }
+    }
+
+    let Some(dreg) = dreg else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "modifying packets is not supported");
+    };
+    let (Some(base), Some(offset), Some(len)) = (base, offset, len) else {
+        return_errno_with_message!(Errno::EINVAL, "the payload is not specified");
+    };
+
+    Ok(Expr::Payload {
+        base: PayloadBase::try_from(base).map_err(|_| {
+            Error::with_message(Errno::EOPNOTSUPP, "the payload base is not supported")
+        })?,
+        offset,
+        len,
+        dreg: Register::new(dreg, len as usize)?,
+    })
+}
+
+fn parse_meta(data: &Nested) -> Result<Expr> {
+    let mut dreg = None;
+    let mut key = None;
+    for attr in data.parse::<MetaAttr>()? {
+        match attr {
+            MetaAttr::Dreg(reg) => dreg = Some(reg.get()),
+            MetaAttr::Key(meta_key) => key = Some(meta_key.get()),
+        }
+    }
+
+    let Some(dreg) = dreg else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "setting metadata is not supported");
+    };
+    let Some(key) = key else {
+        return_errno_with_message!(Errno::EINVAL, "the metadata key is not specified");
+    };
+    let key = MetaKey::try_from(key)
+        .map_err(|_| Error::with_message(Errno::EOPNOTSUPP, "the metadata key is not supported"))?;
+
+    Ok(Expr::Meta {
+        key,
+        dreg: Register::new(dreg, key.data_len())?,
+    })
+}
+
+fn parse_ct(data: &Nested) -> Result<Expr> {
+    let mut dreg = None;
+    let mut key = None;
+    for attr in data.parse::<CtAttr>()? {
+        match attr {
+            CtAttr::Dreg(reg) => dreg = Some(reg.get()),
+            CtAttr::Key(ct_key) => key = Some(ct_key.get()),
+        }
+    }
+
+    let Some(dreg) = dreg else {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "setting connection tracking information is not supported"
+        );
+    };
+    let Some(key) = key else {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the connection tracking key is not specified"
+        );
+    };
+    let key = CtKey::try_from(key).map_err(|_| {
+        Error::with_message(
+            Errno::EOPNOTSUPP,
+            "the connection tracking key is not supported",
+        )
+    })?;
+
+    Ok(Expr::Ct {
+        key,
+        dreg: Register::new(dreg, key.data_len())?,
+    })
+}
+
+fn parse_counter(data: &Nested) -> Result<Expr> {
+    let mut packets = 0;
+    let mut bytes = 0;
+    for attr in data.parse::<CounterAttr>()? {
+        match attr {
+            CounterAttr::Packets(counter_packets) => packets = counter_packets.get(),
+            CounterAttr::Bytes(counter_bytes) => bytes = counter_bytes.get(),
+        }
+    }
+
+    Ok(Expr::Counter(Arc::new(Counter::new(packets, bytes))))
+}
+
+/// The reject types (i.e., `NFT_REJECT_*`).
+const NFT_REJECT_ICMP_UNREACH: u32 = 0;
+const NFT_REJECT_TCP_RST: u32 = 1;
+const NFT_REJECT_ICMPX_UNREACH: u32 = 2;
+
+/// The family-independent ICMP codes (i.e., `NFT_REJECT_ICMPX_*`), indexed by their values.
+const ICMPX_CODES: [IcmpUnreachable; 4] = [
+    IcmpUnreachable::NoRoute,
+    IcmpUnreachable::Port,
+    IcmpUnreachable::Host,
+    IcmpUnreachable::AdminProhibited,
+];
+const NFT_REJECT_ICMPX_PORT_UNREACH: u8 = 1;
+
+/// The ICMP codes (i.e., `ICMP_*`) that are used in Destination Unreachable messages.
+const ICMP_NET_UNREACH: u8 = 0;
+const ICMP_HOST_UNREACH: u8 = 1;
+const ICMP_PORT_UNREACH: u8 = 3;
+const ICMP_PKT_FILTERED: u8 = 13;
+
+/// The ICMPv6 codes (i.e., `ICMPV6_*`) that are used in Destination Unreachable messages.
+const ICMPV6_NOROUTE: u8 = 0;
+const ICMPV6_ADM_PROHIBITED: u8 = 1;
+const ICMPV6_ADDR_UNREACH: u8 = 3;
+const ICMPV6_PORT_UNREACH: u8 = 4;
+
+fn parse_reject(data: &Nested, family: NfProto) -> Result<Expr> {
+    let mut type_ = None;
+    let mut code = None;
+    for attr in data.parse::<RejectAttr>()? {
+        match attr {
+            RejectAttr::Type(reject_type) => type_ = Some(reject_type.get()),
+            RejectAttr::IcmpCode(icmp_code) => code = Some(icmp_code),
+        }
+    }
+
+    let Some(type_) = type_ else {
+        return_errno_with_message!(Errno::EINVAL, "the reject type is not specified");
+    };
+
+    let with = match type_ {
+        NFT_REJECT_TCP_RST => RejectWith::TcpReset,
+        NFT_REJECT_ICMPX_UNREACH => {
+            let code = code.unwrap_or(NFT_REJECT_ICMPX_PORT_UNREACH);
+            let Some(unreachable) = ICMPX_CODES.get(code as usize) else {
+                return_errno_with_message!(Errno::EINVAL, "the ICMP code is invalid");
+            };
+            RejectWith::IcmpUnreachable(*unreachable)
+        }
+        NFT_REJECT_ICMP_UNREACH => {
+            // Like Linux, the default code is the code for unreachable ports.
+            let unreachable = match family {
+                NfProto::Ipv4 => match code.unwrap_or(ICMP_PORT_UNREACH) {
+                    ICMP_NET_UNREACH => IcmpUnreachable::NoRoute,
+                    ICMP_HOST_UNREACH => IcmpUnreachable::Host,
+                    ICMP_PORT_UNREACH => IcmpUnreachable::Port,
+                    ICMP_PKT_FILTERED => IcmpUnreachable::AdminProhibited,
+                    _ => return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the ICMP code is not supported"
+                    ),
+                },
+                NfProto::Ipv6 => match code.unwrap_or(ICMPV6_PORT_UNREACH) {
+                    ICMPV6_NOROUTE => IcmpUnreachable::NoRoute,
+                    ICMPV6_ADDR_UNREACH => IcmpUnreachable::Host,
+                    ICMPV6_PORT_UNREACH => IcmpUnreachable::Port,
+                    ICMPV6_ADM_PROHIBITED => IcmpUnreachable::AdminProhibited,
+                    _ => return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "the ICMPv6 code is not supported"
+                    ),
+                },
+                NfProto::Inet => return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "the inet family only supports family-independent ICMP codes"
+                ),
+            };
+            RejectWith::IcmpUnreachable(unreachable)
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the reject type is invalid"),
+    };
+
+    Ok(Expr::Reject(with))
+}
+
+fn parse_nat(data: &Nested, family: NfProto) -> Result<Expr> {
+    let mut type_ = None;
+    let mut nat_family = None;
+    let mut addr_regs = (None, None);
+    let mut port_regs = (None, None);
+    let mut flags = 0;
+    for attr in data.parse::<NatAttr>()? {
+        match attr {
+            NatAttr::Type(nat_type) => type_ = Some(nat_type.get()),
+            NatAttr::Family(nat_proto) => nat_family = Some(nat_proto.get()),
+            NatAttr::RegAddrMin(reg) => addr_regs.0 = Some(reg.get()),
+            NatAttr::RegAddrMax(reg) => addr_regs.1 = Some(reg.get()),
+            NatAttr::RegProtoMin(reg) => port_regs.0 = Some(reg.get()),
+            NatAttr::RegProtoMax(reg) => port_regs.1 = Some(reg.get()),
+            NatAttr::Flags(nat_flags) => flags = nat_flags.get(),
+        }
+    }
+
+    let Some(type_) = type_ else {
+        return_errno_with_message!(Errno::EINVAL, "the NAT type is not specified");
+    };
+    let Some(nat_family) = nat_family else {
+        return_errno_with_message!(Errno::EINVAL, "the NAT family is not specified");
+    };
+    let type_ = NatType::try_from(type_)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the NAT type is invalid"))?;
+    let nat_family = u8::try_from(nat_family)
+        .ok()
+        .and_then(|nat_family| NfProto::try_from(nat_family).ok())
+        .ok_or_else(|| Error::with_message(Errno::EOPNOTSUPP, "the NAT family is not supported"))?;
+    if family != NfProto::Inet && family != nat_family {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the NAT family does not match the table");
+    }
+
+    let addrs = match nat_family {
+        NfProto::Ipv4 => parse_range_regs(addr_regs, 4)?,
+        NfProto::Ipv6 => parse_range_regs(addr_regs, 16)?,
+        NfProto::Inet if addr_regs.0.is_some() => {
+            return_errno_with_message!(Errno::EAFNOSUPPORT, "the address family is not specified")
+        }
+        NfProto::Inet => None,
+    };
+    let ports = parse_range_regs(port_regs, size_of::<u16>())?;
+
+    Ok(Expr::Nat {
+        type_,
+        family: nat_family,
+        addrs,
+        ports,
+        flags: parse_nat_flags(flags, addrs.is_some(), ports.is_some())?,
+    })
+}
+
+fn parse_masq(data: &Nested) -> Result<Expr> {
+    let mut port_regs = (None, None);
+    let mut flags = 0;
+    for attr in data.parse::<MasqAttr>()? {
+        match attr {
+            MasqAttr::Flags(masq_flags) => flags = masq_flags.get(),
+            MasqAttr::RegProtoMin(reg) => port_regs.0 = Some(reg.get()),
+            MasqAttr::RegProtoMax(reg) => port_regs.1 = Some(reg.get()),
+        }
+    }
+
+    let ports = parse_range_regs(port_regs, size_of::<u16>())?;
+
+    Ok(Expr::Masq {
+        ports,
+        flags: parse_nat_flags(flags, false, ports.is_some())?,
+    })
+}
+
+/// Parses the registers that hold the minimum and the maximum of a range.
+///
+/// Like Linux, the maximum is the same as the minimum if it is not specified.
+fn parse_range_regs(
+    (min, max): (Option<u32>, Option<u32>),
+    len: usize,
+) -> Result<Option<(Register, Register)>> {
+    let Some(min) = min else {
+        return Ok(None);
+    };
+
+    Ok(Some((
+        Register::new(min, len)?,
+        Register::new(max.unwrap_or(min), len)?,
+    )))
+}
+
+/// Parses the NAT flags, adding the flags that indicate whether the addresses and the ports
+/// are specified.
+fn parse_nat_flags(flags: u32, has_addrs: bool, has_ports: bool) -> Result<NatFlags> {
+    let Some(mut flags) = NatFlags::from_bits(flags) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the NAT flags are not supported");
+    };
+
+    flags.set(NatFlags::MAP_IPS, has_addrs);
+    flags.set(NatFlags::PROTO_SPECIFIED, has_ports);
+    Ok(flags)
+}
+
+/// The verdict codes (i.e., `NF_*` and `NFT_*`).
+const NF_DROP: i32 = 0;
+const NF_ACCEPT: i32 = 1;
+const NFT_CONTINUE: i32 = -1;
+const NFT_BREAK: i32 = -2;
+const NFT_JUMP: i32 = -3;
+const NFT_GOTO: i32 = -4;
+const NFT_RETURN: i32 = -5;
+
+fn parse_verdict(data: &Nested) -> Result<Verdict> {
+    let Some(DataAttr::Verdict(verdict)) = data.parse::<DataAttr>()?.into_iter().next() else {
+        return_errno_with_message!(Errno::EINVAL, "the verdict is not specified");
+    };
+
+    let mut code = None;
+    let mut chain = None;
+    for attr in verdict.parse::<VerdictAttr>()? {
+        match attr {
+            VerdictAttr::Code(verdict_code) => code = Some(verdict_code.get() as i32),
+            VerdictAttr::Chain(verdict_chain) => chain = Some(verdict_chain),
+        }
+    }
+
+    let Some(code) = code else {
+        return_errno_with_message!(Errno::EINVAL, "the verdict code is not specified");
+    };
+
+    let verdict = match code {
+        NF_DROP => Verdict::Drop,
+        NF_ACCEPT => Verdict::Accept,
+        NFT_CONTINUE => Verdict::Continue,
+        NFT_BREAK => Verdict::Break,
+        NFT_RETURN => Verdict::Return,
+        NFT_JUMP | NFT_GOTO => {
+            let Some(chain) = chain else {
+                return_errno_with_message!(Errno::EINVAL, "the target chain is not specified");
+            };
+            let chain = name_to_str(&chain)?.to_string();
+            if code == NFT_JUMP {
+                Verdict::Jump(chain)
+            } else {
+                Verdict::Goto(chain)
+            }
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the verdict code is invalid"),
+    };
+
+    Ok(verdict)
+}
+
+fn parse_value(data: &Nested) -> Result<Vec<u8>> {
+    let Some(DataAttr::Value(value)) = data.parse::<DataAttr>()?.into_iter().next() else {
+        return_errno_with_message!(Errno::EINVAL, "the value is not specified");
+    };
+
+    Ok(value)
+}
+
+fn expr_to_attr(expr: &Expr) -> Nested {
+    let (name, data) = match expr {
+        Expr::Payload {
+            base,
+            offset,
+            len,
+            dreg,
+        } => {
+            let attrs = [
+                PayloadAttr::Dreg(Be32::new(dreg.number())),
+                PayloadAttr::Base(Be32::new(*base as u32)),
+                PayloadAttr::Offset(Be32::new(*offset)),
+                PayloadAttr::Len(Be32::new(*len)),
+            ];
+            (EXPR_PAYLOAD, Nested::new(&attrs))
+        }
+        Expr::Meta { key, dreg } => {
+            let attrs = [
+                MetaAttr::Dreg(Be32::new(dreg.number())),
+                MetaAttr::Key(Be32::new(*key as u32)),
+            ];
+            (EXPR_META, Nested::new(&attrs))
+        }
+        Expr::Ct { key, dreg } => {
+            let attrs = [
+                CtAttr::Dreg(Be32::new(dreg.number())),
+                CtAttr::Key(Be32::new(*key as u32)),
+            ];
+            (EXPR_CT, Nested::new(&attrs))
+        }
+        Expr::Cmp { sreg, op, data } => {
+            let attrs = [
+                CmpAttr::Sreg(Be32::new(sreg.number())),
+                CmpAttr::Op(Be32::new(*op as u32)),
+                CmpAttr::Data(value_to_attr(data)),
+            ];
+            (EXPR_CMP, Nested::new(&attrs))
+        }
+        Expr::Bitwise {
+            sreg,
+            dreg,
+            mask,
+            xor,
+        } => {
+            let attrs = [
+                BitwiseAttr::Sreg(Be32::new(sreg.number())),
+                BitwiseAttr::Dreg(Be32::new(dreg.number())),
+                BitwiseAttr::Len(Be32::new(mask.len() as u32)),
+                BitwiseAttr::Mask(value_to_attr(mask)),
+                BitwiseAttr::Xor(value_to_attr(xor)),
+                BitwiseAttr::Op(Be32::new(NFT_BITWISE_BOOL)),
+            ];
+            (EXPR_BITWISE, Nested::new(&attrs))
+        }
+        Expr::Immediate(ImmediateData::Value { dreg, value }) => {
+            let attrs = [
+                ImmediateAttr::Dreg(Be32::new(dreg.number())),
+                ImmediateAttr::Data(value_to_attr(value)),
+            ];
+            (EXPR_IMMEDIATE, Nested::new(&attrs))
+        }
+        Expr::Immediate(ImmediateData::Verdict(verdict)) => {
+            let attrs = [
+                ImmediateAttr::Dreg(Be32::new(NFT_REG_VERDICT)),
+                ImmediateAttr::Data(verdict_to_attr(verdict)),
+            ];
+            (EXPR_IMMEDIATE, Nested::new(&attrs))
+        }
+        Expr::Counter(counter) => {
+            let (packets, bytes) = counter.get();
+            let attrs = [
+                CounterAttr::Bytes(Be64::new(bytes)),
+                CounterAttr::Packets(Be64::new(packets)),
+            ];
+            (EXPR_COUNTER, Nested::new(&attrs))
+        }
+        Expr::Reject(with) => {
+            let attrs = match with {
+                RejectWith::TcpReset => vec![RejectAttr::Type(Be32::new(NFT_REJECT_TCP_RST))],
+                RejectWith::IcmpUnreachable(unreachable) => {
+                    let code = ICMPX_CODES
+                        .iter()
+                        .position(|code| code == unreachable)
+                        .unwrap();
+                    vec![
+                        RejectAttr::Type(Be32::new(NFT_REJECT_ICMPX_UNREACH)),
+                        RejectAttr::IcmpCode(code as u8),
+                    ]
+                }
+            };
+            (EXPR_REJECT, Nested::new(&attrs))
+        }
+        Expr::Nat {
+            type_,
+            family,
+            addrs,
+            ports,
+            flags,
+        } => {
+            let mut attrs = vec![
+                NatAttr::Type(Be32::new(*type_ as u32)),
+                NatAttr::Family(Be32::new(*family as u32)),
+            ];
+            if let Some((min, max)) = addrs {
+                attrs.push(NatAttr::RegAddrMin(Be32::new(min.number())));
+                attrs.push(NatAttr::RegAddrMax(Be32::new(max.number())));
+            }
+            if let Some((min, max)) = ports {
+                attrs.push(NatAttr::RegProtoMin(Be32::new(min.number())));
+                attrs.push(NatAttr::RegProtoMax(Be32::new(max.number())));
+            }
+            if !flags.is_empty() {
+                attrs.push(NatAttr::Flags(Be32::new(flags.bits())));
+            }
+            (EXPR_NAT, Nested::new(&attrs))
+        }
+        Expr::Masq { ports, flags } => {
+            let mut attrs = Vec::new();
+            if !flags.is_empty() {
+                attrs.push(MasqAttr::Flags(Be32::new(flags.bits())));
+            }
+            if let Some((min, max)) = ports {
+                attrs.push(MasqAttr::RegProtoMin(Be32::new(min.number())));
+                attrs.push(MasqAttr::RegProtoMax(Be32::new(max.number())));
+            }
+            (EXPR_MASQ, Nested::new(&attrs))
+        }
+    };
+
+    Nested::new(&[ExprAttr::Name(str_to_name(name)), ExprAttr::Data(data)])
+}
+
+fn value_to_attr(value: &[u8]) -> Nested {
+    Nested::new(&[DataAttr::Value(value.to_vec())])
+}
+
+fn verdict_to_attr(verdict: &Verdict) -> Nested {
+    let (code, chain) = match verdict {
+        Verdict::Accept => (NF_ACCEPT, None),
+        Verdict::Drop => (NF_DROP, None),
+        Verdict::Continue => (NFT_CONTINUE, None),
+        Verdict::Break => (NFT_BREAK, None),
+        Verdict::Jump(chain) => (NFT_JUMP, Some(chain)),
+        Verdict::Goto(chain) => (NFT_GOTO, Some(chain)),
+        Verdict::Return => (NFT_RETURN, None),
+    };
+
+    let mut attrs = vec![VerdictAttr::Code(Be32::new(code as u32))];
+    if let Some(chain) = chain {
+        attrs.push(VerdictAttr::Chain(str_to_name(chain)));
+    }
+
+    Nested::new(&[DataAttr::Verdict(Nested::new(&attrs))])
+}
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle generation-related requests.
+
+use super::util::new_response_header;
+use crate::{
+    net::{
+        netfilter,
+        socket::netlink::netfilter::message::{
+            Be32, GenAttr, GenSegment, NfGenMsgBody, NfnlSegment, NftMsgType,
+        },
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+};
+
+pub(super) fn do_get_gen(request_segment: &GenSegment) -> Result<Vec<NfnlSegment>> {
+    let header = new_response_header(request_segment.header(), NftMsgType::NEWGEN);
+
+    let generation = netfilter::generation();
+    let body = NfGenMsgBody {
+        family: 0,
+        res_id: generation as u16,
+    };
+
+    let mut attrs = vec![
+        GenAttr::Id(Be32::new(generation)),
+        GenAttr::ProcPid(Be32::new(current!().pid())),
+    ];
+    let current = current_thread!();
+    let thread_name = current.as_posix_thread().unwrap().thread_name().lock();
+    if let Some(Ok(Some(name))) = thread_name.as_ref().map(|thread_name| thread_name.name()) {
+        attrs.push(GenAttr::ProcName(name.to_owned()));
+    }
+
+    Ok(vec![NfnlSegment::NewGen(GenSegment::new(
+        header, body, attrs,
+    ))])
+}
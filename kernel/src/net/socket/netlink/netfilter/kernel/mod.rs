@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines the kernel socket,
+//! which is responsible for handling requests from user space.
+//!
+//! Requests that modify the rule set (e.g., `NFT_MSG_NEWRULE`) must be sent in batches, which
+//! begin with `NFNL_MSG_BATCH_BEGIN` and end with `NFNL_MSG_BATCH_END`. The requests in a batch
+//! are applied atomically: If any request fails, none of them take effect.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/netfilter/nfnetlink.c>.
+
+use core::marker::PhantomData;
+
+use super::message::{NfnlMessage, NfnlSegment, NFNL_SUBSYS_NFTABLES};
+use crate::{
+    net::{
+        netfilter::Transaction,
+        socket::netlink::message::{ErrorSegment, ProtocolSegment, SegHdrCommonFlags},
+    },
+    prelude::*,
+};
+
+mod chain;
+mod expr;
+mod generation;
+mod rule;
+mod table;
+mod util;
+
+pub(super) struct NetlinkNetfilterKernelSocket {
+    _private: PhantomData<()>,
+}
+
+/// A batch in progress.
+///
+/// Like Linux, a batch that does not end in the same message is aborted.
+struct Batch {
+    transaction: Transaction,
+    has_error: bool,
+}
+
+impl NetlinkNetfilterKernelSocket {
+    const fn new() -> Self {
+        Self {
+            _private: PhantomData,
+        }
+    }
+
+    pub(super) fn request<F: FnMut(NfnlMessage)>(
+        &self,
+        request: &NfnlMessage,
+        mut consume_response: F,
+    ) {
+        debug!("netlink netfilter request: {:?}", request);
+
+        let mut batch: Option<Batch> = None;
+
+        for segment in request.segments() {
+            let request_header = segment.header();
+
+            let response_segments = match segment {
+                NfnlSegment::BatchBegin(request_segment) => {
+                    begin_batch(&mut batch, request_segment.body().res_id)
+                }
+                NfnlSegment::BatchEnd(_) => end_batch(&mut batch),
+
+                NfnlSegment::NewTable(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| table::do_new_table(tx, request_segment))
+                }
+                NfnlSegment::DelTable(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| table::do_del_table(tx, request_segment))
+                }
+                NfnlSegment::NewChain(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| chain::do_new_chain(tx, request_segment))
+                }
+                NfnlSegment::DelChain(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| chain::do_del_chain(tx, request_segment))
+                }
+                NfnlSegment::NewRule(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| rule::do_new_rule(tx, request_segment))
+                }
+                NfnlSegment::DelRule(request_segment) => {
+                    apply_in_batch(&mut batch, |tx| rule::do_del_rule(tx, request_segment))
+                }
+
+                NfnlSegment::GetTable(request_segment) => {
+                    reply_outside_batch(&mut batch, || table::do_get_table(request_segment))
+                }
+                NfnlSegment::GetChain(request_segment) => {
+                    reply_outside_batch(&mut batch, || chain::do_get_chain(request_segment))
+                }
+                NfnlSegment::GetRule(request_segment) => {
+                    reply_outside_batch(&mut batch, || rule::do_get_rule(request_segment))
+                }
+                NfnlSegment::GetGen(request_segment) => {
+                    reply_outside_batch(&mut batch, || generation::do_get_gen(request_segment))
+                }
+
+                NfnlSegment::NewGen(_)
+                | NfnlSegment::Unsupported(_)
+                | NfnlSegment::Done(_)
+                | NfnlSegment::Error(_) => {
+                    warn!("unsupported request type: {:#x}", request_header.type_);
+                    apply_in_batch(&mut batch, |_| {
+                        return_errno_with_message!(
+                            Errno::EOPNOTSUPP,
+                            "the request type is not supported"
+                        )
+                    })
+                }
+            };
+
+            let response = match response_segments {
+                // Requests that modify the kernel state have no response segments. Their results
+                // are reported only if the `ACK` flag is set.
+                Ok(segments) if segments.is_empty() => {
+                    let flags = SegHdrCommonFlags::from_bits_truncate(request_header.flags);
+                    if !flags.contains(SegHdrCommonFlags::ACK) {
+                        continue;
+                    }
+                    let ack_segment = ErrorSegment::new_from_request(request_header, None);
+                    NfnlMessage::new(vec![NfnlSegment::Error(ack_segment)])
+                }
+                Ok(segments) => NfnlMessage::new(segments),
+                Err(error) => {
+                    let err_segment = ErrorSegment::new_from_request(request_header, Some(error));
+                    NfnlMessage::new(vec![NfnlSegment::Error(err_segment)])
+                }
+            };
+
+            debug!("netlink netfilter response: {:?}", response);
+
+            consume_response(response);
+        }
+    }
+}
+
+fn begin_batch(batch: &mut Option<Batch>, res_id: u16) -> Result<Vec<NfnlSegment>> {
+    if batch.is_some() {
+        return_errno_with_message!(Errno::EINVAL, "batches cannot be nested");
+    }
+    if res_id != NFNL_SUBSYS_NFTABLES {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "only the nf_tables subsystem supports batches"
+        );
+    }
+    util::check_net_admin()?;
+
+    *batch = Some(Batch {
+        transaction: Transaction::begin(),
+        has_error: false,
+    });
+    Ok(Vec::new())
+}
+
+fn end_batch(batch: &mut Option<Batch>) -> Result<Vec<NfnlSegment>> {
+    let Some(Batch {
+        transaction,
+        has_error,
+    }) = batch.take()
+    else {
+        return_errno_with_message!(Errno::EINVAL, "there is no batch to end");
+    };
+
+    // The changes are discarded if any request in the batch fails.
+    if !has_error {
+        transaction.commit();
+    }
+    Ok(Vec::new())
+}
+
+/// Applies a request that modifies the rule set to the transaction of the batch.
+fn apply_in_batch(
+    batch: &mut Option<Batch>,
+    f: impl FnOnce(&mut Transaction) -> Result<()>,
+) -> Result<Vec<NfnlSegment>> {
+    let Some(batch) = batch.as_mut() else {
+        return_errno_with_message!(Errno::EINVAL, "the request must be sent in a batch");
+    };
+
+    let result = f(&mut batch.transaction);
+    if result.is_err() {
+        batch.has_error = true;
+    }
+    result.map(|_| Vec::new())
+}
+
+/// Replies to a request that does not modify the rule set.
+fn reply_outside_batch(
+    batch: &mut Option<Batch>,
+    f: impl FnOnce() -> Result<Vec<NfnlSegment>>,
+) -> Result<Vec<NfnlSegment>> {
+    if let Some(batch) = batch.as_mut() {
+        batch.has_error = true;
+        return_errno_with_message!(Errno::EINVAL, "the request cannot be sent in a batch");
+    }
+
+    f()
+}
+
+/// FIXME: NETLINK_NETFILTER_KERNEL should be a per-network namespace socket
+static NETLINK_NETFILTER_KERNEL: NetlinkNetfilterKernelSocket = NetlinkNetfilterKernelSocket::new();
+
+pub(super) fn get_netlink_netfilter_kernel() -> &'static NetlinkNetfilterKernelSocket {
+    &NETLINK_NETFILTER_KERNEL
+}
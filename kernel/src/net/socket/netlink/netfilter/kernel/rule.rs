@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle rule-related requests.
+
+use super::{
+    expr::{exprs_to_attr, parse_exprs},
+    util::{
+        finish_response, is_dump_request, name_to_str, new_response_body, new_response_header,
+        parse_family, parse_specific_family, str_to_name,
+    },
+};
+use crate::{
+    net::{
+        netfilter::{self, Chain, Rule, Table, Transaction},
+        socket::netlink::{
+            message::{CMsgSegHdr, NewRequestFlags},
+            netfilter::message::{Be64, Nested, NfnlSegment, NftMsgType, RuleAttr, RuleSegment},
+        },
+    },
+    prelude::*,
+};
+
+pub(super) fn do_new_rule(tx: &mut Transaction, request_segment: &RuleSegment) -> Result<()> {
+    let family = parse_specific_family(request_segment.body().family)?;
+    let request = RuleRequest::parse(request_segment)?;
+
+    let (Some(table_name), Some(chain_name)) = (request.table, request.chain) else {
+        return_errno_with_message!(Errno::EINVAL, "the chain is not specified");
+    };
+    let exprs = match request.exprs {
+        Some(exprs) => parse_exprs(exprs, family)?,
+        None => Vec::new(),
+    };
+
+    let table = tx.table_mut(family, table_name)?;
+    // Check that the chain exists before checking the jump targets in the rule.
+    table.chain(chain_name)?;
+    let rule = table.new_rule(chain_name, exprs)?;
+    let chain = table.chain_mut(chain_name)?;
+
+    let request_flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    if let Some(handle) = request.handle {
+        if !request_flags.contains(NewRequestFlags::REPLACE) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "rules cannot be updated");
+        }
+        let index = chain.rule_index(handle)?;
+        chain.replace_rule(index, rule);
+        return Ok(());
+    }
+
+    // Like Linux, new rules are inserted before the position (or at the beginning) by default,
+    // and are appended after the position (or at the end) with `NLM_F_APPEND`.
+    let is_append = request_flags.contains(NewRequestFlags::APPEND);
+    let index = match (request.position, is_append) {
+        (Some(position), false) => chain.rule_index(position)?,
+        (Some(position), true) => chain.rule_index(position)? + 1,
+        (None, false) => 0,
+        (None, true) => chain.rules().len(),
+    };
+    chain.insert_rule(index, rule);
+
+    Ok(())
+}
+
+pub(super) fn do_del_rule(tx: &mut Transaction, request_segment: &RuleSegment) -> Result<()> {
+    let family = parse_specific_family(request_segment.body().family)?;
+    let request = RuleRequest::parse(request_segment)?;
+
+    let Some(table_name) = request.table else {
+        return_errno_with_message!(Errno::EINVAL, "the table name is not specified");
+    };
+    let table = tx.table_mut(family, table_name)?;
+
+    let Some(chain_name) = request.chain else {
+        // Like Linux, the request flushes all chains in the table if the chain is not specified.
+        table.flush();
+        return Ok(());
+    };
+    let chain = table.chain_mut(chain_name)?;
+
+    match request.handle {
+        Some(handle) => {
+            let index = chain.rule_index(handle)?;
+            chain.remove_rule(index);
+        }
+        None => chain.flush(),
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_get_rule(request_segment: &RuleSegment) -> Result<Vec<NfnlSegment>> {
+    let family = parse_family(request_segment.body().family)?;
+    let request = RuleRequest::parse(request_segment)?;
+    let request_header = request_segment.header();
+    let dump_all = is_dump_request(request_header);
+
+    let mut response_segments: Vec<NfnlSegment> = netfilter::with_ruleset(|ruleset| {
+        if dump_all {
+            let chain_filter = request.chain;
+            return Ok(ruleset
+                .tables()
+                .iter()
+                .filter(|table| family.is_none_or(|family| table.family() == family))
+                .filter(|table| request.table.is_none_or(|name| table.name() == name))
+                .flat_map(move |table| {
+                    table
+                        .chains()
+                        .iter()
+                        .filter(move |chain| chain_filter.is_none_or(|name| chain.name() == name))
+                        .flat_map(move |chain| {
+                            chain.rules().iter().map(move |rule| {
+                                rule_to_new_rule(request_header, table, chain, rule)
+                            })
+                        })
+                })
+                .collect());
+        }
+
+        let (Some(family), Some(table_name), Some(chain_name), Some(handle)) =
+            (family, request.table, request.chain, request.handle)
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the rule is not specified");
+        };
+        let table = ruleset.table(family, table_name)?;
+        let chain = table.chain(chain_name)?;
+        let rule = &chain.rules()[chain.rule_index(handle)?];
+
+        Ok(vec![rule_to_new_rule(request_header, table, chain, rule)])
+    })?;
+
+    finish_response(request_header, dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+/// The attributes of a rule-related request.
+struct RuleRequest<'a> {
+    table: Option<&'a str>,
+    chain: Option<&'a str>,
+    handle: Option<u64>,
+    exprs: Option<&'a Nested>,
+    position: Option<u64>,
+}
+
+impl<'a> RuleRequest<'a> {
+    fn parse(request_segment: &'a RuleSegment) -> Result<Self> {
+        let mut request = Self {
+            table: None,
+            chain: None,
+            handle: None,
+            exprs: None,
+            position: None,
+        };
+
+        for attr in request_segment.attrs() {
+            match attr {
+                RuleAttr::Table(table) => request.table = Some(name_to_str(table)?),
+                RuleAttr::Chain(chain) => request.chain = Some(name_to_str(chain)?),
+                RuleAttr::Handle(handle) => request.handle = Some(handle.get()),
+                RuleAttr::Expressions(exprs) => request.exprs = Some(exprs),
+                RuleAttr::Position(position) => request.position = Some(position.get()),
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+fn rule_to_new_rule(
+    request_header: &CMsgSegHdr,
+    table: &Table,
+    chain: &Chain,
+    rule: &Rule,
+) -> NfnlSegment {
+    let header = new_response_header(request_header, NftMsgType::NEWRULE);
+
+    let attrs = vec![
+        RuleAttr::Table(str_to_name(table.name())),
+        RuleAttr::Chain(str_to_name(chain.name())),
+        RuleAttr::Handle(Be64::new(rule.handle())),
+        RuleAttr::Expressions(exprs_to_attr(rule.exprs())),
+    ];
+
+    NfnlSegment::NewRule(RuleSegment::new(
+        header,
+        new_response_body(table.family()),
+        attrs,
+    ))
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle table-related requests.
+
+use super::util::{
+    finish_response, is_dump_request, name_to_str, new_response_body, new_response_header,
+    parse_family, parse_specific_family, str_to_name,
+};
+use crate::{
+    net::{
+        netfilter::{self, Table, Transaction},
+        socket::netlink::{
+            message::{CMsgSegHdr, NewRequestFlags},
+            netfilter::message::{
+                Be32, Be64, NfnlSegment, NftMsgType, TableAttr, TableFlags, TableSegment,
+            },
+        },
+    },
+    prelude::*,
+};
+
+pub(super) fn do_new_table(tx: &mut Transaction, request_segment: &TableSegment) -> Result<()> {
+    let family = parse_specific_family(request_segment.body().family)?;
+
+    let mut name = None;
+    let mut flags = None;
+    for attr in request_segment.attrs() {
+        match attr {
+            TableAttr::Name(table_name) => name = Some(name_to_str(table_name)?),
+            TableAttr::Flags(table_flags) => flags = Some(table_flags.get()),
+            _ => (),
+        }
+    }
+    let Some(name) = name else {
+        return_errno_with_message!(Errno::EINVAL, "the table name is not specified");
+    };
+    let is_dormant = match flags.map(TableFlags::from_bits) {
+        None => None,
+        Some(Some(TableFlags::DORMANT)) => Some(true),
+        Some(Some(flags)) if flags.is_empty() => Some(false),
+        Some(_) => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the table flags are not supported")
+        }
+    };
+
+    let Ok(table) = tx.table_mut(family, name) else {
+        return tx.add_table(family, name.to_string(), is_dormant.unwrap_or(false));
+    };
+
+    let request_flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    if request_flags.contains(NewRequestFlags::EXCL) {
+        return_errno_with_message!(Errno::EEXIST, "the table already exists");
+    }
+    if request_flags.contains(NewRequestFlags::REPLACE) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "tables cannot be replaced");
+    }
+    if let Some(is_dormant) = is_dormant {
+        table.set_dormant(is_dormant);
+    }
+
+    Ok(())
+}
+
+pub(super) fn do_del_table(tx: &mut Transaction, request_segment: &TableSegment) -> Result<()> {
+    let family = parse_family(request_segment.body().family)?;
+
+    let mut name = None;
+    let mut handle = None;
+    for attr in request_segment.attrs() {
+        match attr {
+            TableAttr::Name(table_name) => name = Some(name_to_str(table_name)?),
+            TableAttr::Handle(table_handle) => handle = Some(table_handle.get()),
+            _ => (),
+        }
+    }
+
+    // Like Linux, the request flushes the rule set if the family or the table is not specified.
+    let Some(family) = family else {
+        tx.clear(None);
+        return Ok(());
+    };
+    let name = match (name, handle) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(handle)) => tx
+            .tables()
+            .iter()
+            .find(|table| table.family() == family && table.handle() == handle)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the table does not exist"))?
+            .name()
+            .to_string(),
+        (None, None) => {
+            tx.clear(Some(family));
+            return Ok(());
+        }
+    };
+
+    tx.remove_table(family, &name)
+}
+
+pub(super) fn do_get_table(request_segment: &TableSegment) -> Result<Vec<NfnlSegment>> {
+    let family = parse_family(request_segment.body().family)?;
+    let request_header = request_segment.header();
+    let dump_all = is_dump_request(request_header);
+
+    let mut response_segments: Vec<NfnlSegment> = netfilter::with_ruleset(|ruleset| {
+        if dump_all {
+            return Ok(ruleset
+                .tables()
+                .iter()
+                .filter(|table| family.is_none_or(|family| table.family() == family))
+                .map(|table| table_to_new_table(request_header, table))
+                .collect());
+        }
+
+        let Some(family) = family else {
+            return_errno_with_message!(Errno::EINVAL, "the family is not specified");
+        };
+        let name = request_segment
+            .attrs()
+            .iter()
+            .find_map(|attr| match attr {
+                TableAttr::Name(name) => Some(name),
+                _ => None,
+            })
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the table name is not specified"))?;
+        let table = ruleset.table(family, name_to_str(name)?)?;
+
+        Ok(vec![table_to_new_table(request_header, table)])
+    })?;
+
+    finish_response(request_header, dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+fn table_to_new_table(request_header: &CMsgSegHdr, table: &Table) -> NfnlSegment {
+    let header = new_response_header(request_header, NftMsgType::NEWTABLE);
+
+    let flags = if table.is_dormant() {
+        TableFlags::DORMANT
+    } else {
+        TableFlags::empty()
+    };
+    let attrs = vec![
+        TableAttr::Name(str_to_name(table.name())),
+        TableAttr::Flags(Be32::new(flags.bits())),
+        TableAttr::Use(Be32::new(table.chains().len() as u32)),
+        TableAttr::Handle(Be64::new(table.handle())),
+    ];
+
+    NfnlSegment::NewTable(TableSegment::new(
+        header,
+        new_response_body(table.family()),
+        attrs,
+    ))
+}
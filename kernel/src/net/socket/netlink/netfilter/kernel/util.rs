@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    net::{
+        netfilter::{self, NfProto},
+        socket::netlink::{
+            message::{
+                CMsgSegHdr, DoneSegment, GetRequestFlags, ProtocolSegment, SegHdrCommonFlags,
+            },
+            netfilter::message::{NfGenMsgBody, NfnlSegment, NftMsgType},
+        },
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// Returns whether a get request asks for all entries.
+pub fn is_dump_request(request_header: &CMsgSegHdr) -> bool {
+    let flags = GetRequestFlags::from_bits_truncate(request_header.flags);
+    flags.contains(GetRequestFlags::DUMP)
+}
+
+/// Finishes a response message.
+pub fn finish_response(
+    request_header: &CMsgSegHdr,
+    dump_all: bool,
+    response_segments: &mut Vec<NfnlSegment>,
+) {
+    if !dump_all {
+        assert_eq!(response_segments.len(), 1);
+        return;
+    }
+    append_done_segment(request_header, response_segments);
+    add_multi_flag(response_segments);
+}
+
+/// Appends a done segment as the last segment of the provided segments.
+fn append_done_segment(request_header: &CMsgSegHdr, response_segments: &mut Vec<NfnlSegment>) {
+    let done_segment = DoneSegment::new_from_request(request_header, None);
+    response_segments.push(NfnlSegment::Done(done_segment));
+}
+
+/// Adds the `MULTI` flag to all segments in `segments`.
+fn add_multi_flag(response_segments: &mut Vec<NfnlSegment>) {
+    for segment in response_segments.iter_mut() {
+        let header = segment.header_mut();
+        let mut flags = SegHdrCommonFlags::from_bits_truncate(header.flags);
+        flags |= SegHdrCommonFlags::MULTI;
+        header.flags = flags.bits();
+    }
+}
+
+/// Creates the header of a response segment.
+pub fn new_response_header(request_header: &CMsgSegHdr, msg_type: NftMsgType) -> CMsgSegHdr {
+    CMsgSegHdr {
+        len: 0,
+        type_: msg_type.segment_type(),
+        flags: SegHdrCommonFlags::empty().bits(),
+        seq: request_header.seq,
+        pid: request_header.pid,
+    }
+}
+
+/// Creates the body of a response segment.
+pub fn new_response_body(family: NfProto) -> NfGenMsgBody {
+    NfGenMsgBody {
+        family: family as u8,
+        res_id: netfilter::generation() as u16,
+    }
+}
+
+/// Checks whether the current thread is allowed to modify the network configurations.
+pub fn check_net_admin() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+    if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "modifying network configurations requires `CAP_NET_ADMIN`"
+        );
+    }
+
+    Ok(())
+}
+
+/// Parses the family in a request.
+///
+/// Returns `None` if the family is `NFPROTO_UNSPEC`, which matches all families.
+pub fn parse_family(family: u8) -> Result<Option<NfProto>> {
+    if family == NFPROTO_UNSPEC {
+        return Ok(None);
+    }
+
+    NfProto::try_from(family)
+        .map(Some)
+        .map_err(|_| Error::with_message(Errno::EAFNOSUPPORT, "the family is not supported"))
+}
+
+/// Parses the family in a request that operates on a specific table.
+pub fn parse_specific_family(family: u8) -> Result<NfProto> {
+    parse_family(family)?
+        .ok_or_else(|| Error::with_message(Errno::EAFNOSUPPORT, "the family is not specified"))
+}
+
+const NFPROTO_UNSPEC: u8 = 0;
+
+/// Converts a name in an attribute to a string.
+pub fn name_to_str(name: &CStr) -> Result<&str> {
+    name.to_str()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the name is not valid UTF-8"))
+}
+
+/// Converts a name to the payload of an attribute.
+pub fn str_to_name(name: &str) -> CString {
+    // The names are converted from C strings, so they contain no zeros.
+    CString::new(name).unwrap()
+}
@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{read_name, Be32, Be64, Nested};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Chain-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum ChainAttrClass {
+    UNSPEC = 0,
+    TABLE = 1,
+    HANDLE = 2,
+    NAME = 3,
+    HOOK = 4,
+    POLICY = 5,
+    USE = 6,
+    TYPE = 7,
+    COUNTERS = 8,
+    PAD = 9,
+    FLAGS = 10,
+    ID = 11,
+    USERDATA = 12,
+}
+
+#[derive(Debug)]
+pub enum ChainAttr {
+    Table(CString),
+    Handle(Be64),
+    Name(CString),
+    /// The hook attributes (see [`HookAttr`]).
+    Hook(Nested),
+    Policy(Be32),
+    /// The number of rules that jump to the chain.
+    Use(Be32),
+    Type(CString),
+}
+
+impl ChainAttr {
+    fn class(&self) -> ChainAttrClass {
+        match self {
+            ChainAttr::Table(_) => ChainAttrClass::TABLE,
+            ChainAttr::Handle(_) => ChainAttrClass::HANDLE,
+            ChainAttr::Name(_) => ChainAttrClass::NAME,
+            ChainAttr::Hook(_) => ChainAttrClass::HOOK,
+            ChainAttr::Policy(_) => ChainAttrClass::POLICY,
+            ChainAttr::Use(_) => ChainAttrClass::USE,
+            ChainAttr::Type(_) => ChainAttrClass::TYPE,
+        }
+    }
+}
+
+impl Attribute for ChainAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            ChainAttr::Table(table) => table.as_bytes_with_nul(),
+            ChainAttr::Handle(handle) => handle.as_bytes(),
+            ChainAttr::Name(name) => name.as_bytes_with_nul(),
+            ChainAttr::Hook(hook) => hook.as_bytes(),
+            ChainAttr::Policy(policy) => policy.as_bytes(),
+            ChainAttr::Use(use_) => use_.as_bytes(),
+            ChainAttr::Type(type_) => type_.as_bytes_with_nul(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match ChainAttrClass::try_from(header.type_()) {
+            Ok(ChainAttrClass::TABLE) => Self::Table(read_name(header, reader)?),
+            Ok(ChainAttrClass::HANDLE) => Self::Handle(reader.read_val()?),
+            Ok(ChainAttrClass::NAME) => Self::Name(read_name(header, reader)?),
+            Ok(ChainAttrClass::HOOK) => Self::Hook(Nested::read_from(header, reader)?),
+            Ok(ChainAttrClass::POLICY) => Self::Policy(reader.read_val()?),
+            Ok(ChainAttrClass::TYPE) => Self::Type(read_name(header, reader)?),
+            class => {
+                debug!("chain attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Hook-related attributes, which are nested in [`ChainAttr::Hook`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum HookAttrClass {
+    UNSPEC = 0,
+    HOOKNUM = 1,
+    PRIORITY = 2,
+    DEV = 3,
+    DEVS = 4,
+}
+
+#[derive(Debug)]
+pub enum HookAttr {
+    HookNum(Be32),
+    Priority(Be32),
+}
+
+impl HookAttr {
+    fn class(&self) -> HookAttrClass {
+        match self {
+            HookAttr::HookNum(_) => HookAttrClass::HOOKNUM,
+            HookAttr::Priority(_) => HookAttrClass::PRIORITY,
+        }
+    }
+}
+
+impl Attribute for HookAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            HookAttr::HookNum(hooknum) => hooknum.as_bytes(),
+            HookAttr::Priority(priority) => priority.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match HookAttrClass::try_from(header.type_()) {
+            Ok(HookAttrClass::HOOKNUM) => Self::HookNum(reader.read_val()?),
+            Ok(HookAttrClass::PRIORITY) => Self::Priority(reader.read_val()?),
+            class => {
+                debug!("hook attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
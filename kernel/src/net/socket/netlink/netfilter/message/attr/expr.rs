@@ -0,0 +1,764 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Attributes of the expressions, which are nested in [`ExprAttr::Data`].
+//!
+//! [`ExprAttr::Data`]: super::rule::ExprAttr::Data
+
+use super::{read_bytes, read_name, Be32, Be64, Nested};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Data-related attributes, which hold either values or verdicts.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum DataAttrClass {
+    UNSPEC = 0,
+    VALUE = 1,
+    VERDICT = 2,
+}
+
+#[derive(Debug)]
+pub enum DataAttr {
+    Value(Vec<u8>),
+    /// The verdict attributes (see [`VerdictAttr`]).
+    Verdict(Nested),
+}
+
+impl DataAttr {
+    fn class(&self) -> DataAttrClass {
+        match self {
+            DataAttr::Value(_) => DataAttrClass::VALUE,
+            DataAttr::Verdict(_) => DataAttrClass::VERDICT,
+        }
+    }
+}
+
+impl Attribute for DataAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            DataAttr::Value(value) => value.as_slice(),
+            DataAttr::Verdict(verdict) => verdict.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match DataAttrClass::try_from(header.type_()) {
+            Ok(DataAttrClass::VALUE) => Self::Value(read_bytes(header, reader)?),
+            Ok(DataAttrClass::VERDICT) => Self::Verdict(Nested::read_from(header, reader)?),
+            class => {
+                debug!("data attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Verdict-related attributes, which are nested in [`DataAttr::Verdict`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum VerdictAttrClass {
+    UNSPEC = 0,
+    CODE = 1,
+    CHAIN = 2,
+    CHAIN_ID = 3,
+}
+
+#[derive(Debug)]
+pub enum VerdictAttr {
+    Code(Be32),
+    Chain(CString),
+}
+
+impl VerdictAttr {
+    fn class(&self) -> VerdictAttrClass {
+        match self {
+            VerdictAttr::Code(_) => VerdictAttrClass::CODE,
+            VerdictAttr::Chain(_) => VerdictAttrClass::CHAIN,
+        }
+    }
+}
+
+impl Attribute for VerdictAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            VerdictAttr::Code(code) => code.as_bytes(),
+            VerdictAttr::Chain(chain) => chain.as_bytes_with_nul(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match VerdictAttrClass::try_from(header.type_()) {
+            Ok(VerdictAttrClass::CODE) => Self::Code(reader.read_val()?),
+            Ok(VerdictAttrClass::CHAIN) => Self::Chain(read_name(header, reader)?),
+            class => {
+                debug!("verdict attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `immediate` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum ImmediateAttrClass {
+    UNSPEC = 0,
+    DREG = 1,
+    DATA = 2,
+}
+
+#[derive(Debug)]
+pub enum ImmediateAttr {
+    Dreg(Be32),
+    /// The data attributes (see [`DataAttr`]).
+    Data(Nested),
+}
+
+impl ImmediateAttr {
+    fn class(&self) -> ImmediateAttrClass {
+        match self {
+            ImmediateAttr::Dreg(_) => ImmediateAttrClass::DREG,
+            ImmediateAttr::Data(_) => ImmediateAttrClass::DATA,
+        }
+    }
+}
+
+impl Attribute for ImmediateAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            ImmediateAttr::Dreg(dreg) => dreg.as_bytes(),
+            ImmediateAttr::Data(data) => data.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match ImmediateAttrClass::try_from(header.type_()) {
+            Ok(ImmediateAttrClass::DREG) => Self::Dreg(reader.read_val()?),
+            Ok(ImmediateAttrClass::DATA) => Self::Data(Nested::read_from(header, reader)?),
+            class => {
+                debug!("immediate attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `cmp` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum CmpAttrClass {
+    UNSPEC = 0,
+    SREG = 1,
+    OP = 2,
+    DATA = 3,
+}
+
+#[derive(Debug)]
+pub enum CmpAttr {
+    Sreg(Be32),
+    Op(Be32),
+    /// The data attributes (see [`DataAttr`]).
+    Data(Nested),
+}
+
+impl CmpAttr {
+    fn class(&self) -> CmpAttrClass {
+        match self {
+            CmpAttr::Sreg(_) => CmpAttrClass::SREG,
+            CmpAttr::Op(_) => CmpAttrClass::OP,
+            CmpAttr::Data(_) => CmpAttrClass::DATA,
+        }
+    }
+}
+
+impl Attribute for CmpAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            CmpAttr::Sreg(sreg) => sreg.as_bytes(),
+            CmpAttr::Op(op) => op.as_bytes(),
+            CmpAttr::Data(data) => data.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match CmpAttrClass::try_from(header.type_()) {
+            Ok(CmpAttrClass::SREG) => Self::Sreg(reader.read_val()?),
+            Ok(CmpAttrClass::OP) => Self::Op(reader.read_val()?),
+            Ok(CmpAttrClass::DATA) => Self::Data(Nested::read_from(header, reader)?),
+            class => {
+                debug!("cmp attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `bitwise` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum BitwiseAttrClass {
+    UNSPEC = 0,
+    SREG = 1,
+    DREG = 2,
+    LEN = 3,
+    MASK = 4,
+    XOR = 5,
+    OP = 6,
+    DATA = 7,
+}
+
+#[derive(Debug)]
+pub enum BitwiseAttr {
+    Sreg(Be32),
+    Dreg(Be32),
+    Len(Be32),
+    /// The data attributes (see [`DataAttr`]).
+    Mask(Nested),
+    /// The data attributes (see [`DataAttr`]).
+    Xor(Nested),
+    Op(Be32),
+}
+
+impl BitwiseAttr {
+    fn class(&self) -> BitwiseAttrClass {
+        match self {
+            BitwiseAttr::Sreg(_) => BitwiseAttrClass::SREG,
+            BitwiseAttr::Dreg(_) => BitwiseAttrClass::DREG,
+            BitwiseAttr::Len(_) => BitwiseAttrClass::LEN,
+            BitwiseAttr::Mask(_) => BitwiseAttrClass::MASK,
+            BitwiseAttr::Xor(_) => BitwiseAttrClass::XOR,
+            BitwiseAttr::Op(_) => BitwiseAttrClass::OP,
+        }
+    }
+}
+
+impl Attribute for BitwiseAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            BitwiseAttr::Sreg(sreg) => sreg.as_bytes(),
+            BitwiseAttr::Dreg(dreg) => dreg.as_bytes(),
+            BitwiseAttr::Len(len) => len.as_bytes(),
+            BitwiseAttr::Mask(mask) => mask.as_bytes(),
+            BitwiseAttr::Xor(xor) => xor.as_bytes(),
+            BitwiseAttr::Op(op) => op.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match BitwiseAttrClass::try_from(header.type_()) {
+            Ok(BitwiseAttrClass::SREG) => Self::Sreg(reader.read_val()?),
+            Ok(BitwiseAttrClass::DREG) => Self::Dreg(reader.read_val()?),
+            Ok(BitwiseAttrClass::LEN) => Self::Len(reader.read_val()?),
+            Ok(BitwiseAttrClass::MASK) => Self::Mask(Nested::read_from(header, reader)?),
+            Ok(BitwiseAttrClass::XOR) => Self::Xor(Nested::read_from(header, reader)?),
+            Ok(BitwiseAttrClass::OP) => Self::Op(reader.read_val()?),
+            class => {
+                debug!("bitwise attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `payload` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum PayloadAttrClass {
+    UNSPEC = 0,
+    DREG = 1,
+    BASE = 2,
+    OFFSET = 3,
+    LEN = 4,
+    SREG = 5,
+    CSUM_TYPE = 6,
+    CSUM_OFFSET = 7,
+    CSUM_FLAGS = 8,
+}
+
+#[derive(Debug)]
+pub enum PayloadAttr {
+    Dreg(Be32),
+    Base(Be32),
+    Offset(Be32),
+    Len(Be32),
+}
+
+impl PayloadAttr {
+    fn class(&self) -> PayloadAttrClass {
+        match self {
+            PayloadAttr::Dreg(_) => PayloadAttrClass::DREG,
+            PayloadAttr::Base(_) => PayloadAttrClass::BASE,
+            PayloadAttr::Offset(_) => PayloadAttrClass::OFFSET,
+            PayloadAttr::Len(_) => PayloadAttrClass::LEN,
+        }
+    }
+}
+
+impl Attribute for PayloadAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            PayloadAttr::Dreg(dreg) => dreg.as_bytes(),
+            PayloadAttr::Base(base) => base.as_bytes(),
+            PayloadAttr::Offset(offset) => offset.as_bytes(),
+            PayloadAttr::Len(len) => len.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match PayloadAttrClass::try_from(header.type_()) {
+            Ok(PayloadAttrClass::DREG) => Self::Dreg(reader.read_val()?),
+            Ok(PayloadAttrClass::BASE) => Self::Base(reader.read_val()?),
+            Ok(PayloadAttrClass::OFFSET) => Self::Offset(reader.read_val()?),
+            Ok(PayloadAttrClass::LEN) => Self::Len(reader.read_val()?),
+            class => {
+                debug!("payload attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `meta` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum MetaAttrClass {
+    UNSPEC = 0,
+    DREG = 1,
+    KEY = 2,
+    SREG = 3,
+}
+
+#[derive(Debug)]
+pub enum MetaAttr {
+    Dreg(Be32),
+    Key(Be32),
+}
+
+impl MetaAttr {
+    fn class(&self) -> MetaAttrClass {
+        match self {
+            MetaAttr::Dreg(_) => MetaAttrClass::DREG,
+            MetaAttr::Key(_) => MetaAttrClass::KEY,
+        }
+    }
+}
+
+impl Attribute for MetaAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            MetaAttr::Dreg(dreg) => dreg.as_bytes(),
+            MetaAttr::Key(key) => key.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match MetaAttrClass::try_from(header.type_()) {
+            Ok(MetaAttrClass::DREG) => Self::Dreg(reader.read_val()?),
+            Ok(MetaAttrClass::KEY) => Self::Key(reader.read_val()?),
+            class => {
+                debug!("meta attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `ct` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum CtAttrClass {
+    UNSPEC = 0,
+    DREG = 1,
+    KEY = 2,
+    DIRECTION = 3,
+    SREG = 4,
+}
+
+#[derive(Debug)]
+pub enum CtAttr {
+    Dreg(Be32),
+    Key(Be32),
+}
+
+impl CtAttr {
+    fn class(&self) -> CtAttrClass {
+        match self {
+            CtAttr::Dreg(_) => CtAttrClass::DREG,
+            CtAttr::Key(_) => CtAttrClass::KEY,
+        }
+    }
+}
+
+impl Attribute for CtAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            CtAttr::Dreg(dreg) => dreg.as_bytes(),
+            CtAttr::Key(key) => key.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match CtAttrClass::try_from(header.type_()) {
+            Ok(CtAttrClass::DREG) => Self::Dreg(reader.read_val()?),
+            Ok(CtAttrClass::KEY) => Self::Key(reader.read_val()?),
+            class => {
+                debug!("ct attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `counter` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum CounterAttrClass {
+    UNSPEC = 0,
+    BYTES = 1,
+    PACKETS = 2,
+    PAD = 3,
+}
+
+#[derive(Debug)]
+pub enum CounterAttr {
+    Bytes(Be64),
+    Packets(Be64),
+}
+
+impl CounterAttr {
+    fn class(&self) -> CounterAttrClass {
+        match self {
+            CounterAttr::Bytes(_) => CounterAttrClass::BYTES,
+            CounterAttr::Packets(_) => CounterAttrClass::PACKETS,
+        }
+    }
+}
+
+impl Attribute for CounterAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            CounterAttr::Bytes(bytes) => bytes.as_bytes(),
+            CounterAttr::Packets(packets) => packets.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match CounterAttrClass::try_from(header.type_()) {
+            Ok(CounterAttrClass::BYTES) => Self::Bytes(reader.read_val()?),
+            Ok(CounterAttrClass::PACKETS) => Self::Packets(reader.read_val()?),
+            class => {
+                debug!("counter attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `reject` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum RejectAttrClass {
+    UNSPEC = 0,
+    TYPE = 1,
+    ICMP_CODE = 2,
+}
+
+#[derive(Debug)]
+pub enum RejectAttr {
+    Type(Be32),
+    IcmpCode(u8),
+}
+
+impl RejectAttr {
+    fn class(&self) -> RejectAttrClass {
+        match self {
+            RejectAttr::Type(_) => RejectAttrClass::TYPE,
+            RejectAttr::IcmpCode(_) => RejectAttrClass::ICMP_CODE,
+        }
+    }
+}
+
+impl Attribute for RejectAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            RejectAttr::Type(type_) => type_.as_bytes(),
+            RejectAttr::IcmpCode(icmp_code) => icmp_code.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match RejectAttrClass::try_from(header.type_()) {
+            Ok(RejectAttrClass::TYPE) => Self::Type(reader.read_val()?),
+            Ok(RejectAttrClass::ICMP_CODE) => Self::IcmpCode(reader.read_val()?),
+            class => {
+                debug!("reject attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `nat` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum NatAttrClass {
+    UNSPEC = 0,
+    TYPE = 1,
+    FAMILY = 2,
+    REG_ADDR_MIN = 3,
+    REG_ADDR_MAX = 4,
+    REG_PROTO_MIN = 5,
+    REG_PROTO_MAX = 6,
+    FLAGS = 7,
+}
+
+#[derive(Debug)]
+pub enum NatAttr {
+    Type(Be32),
+    Family(Be32),
+    RegAddrMin(Be32),
+    RegAddrMax(Be32),
+    RegProtoMin(Be32),
+    RegProtoMax(Be32),
+    Flags(Be32),
+}
+
+impl NatAttr {
+    fn class(&self) -> NatAttrClass {
+        match self {
+            NatAttr::Type(_) => NatAttrClass::TYPE,
+            NatAttr::Family(_) => NatAttrClass::FAMILY,
+            NatAttr::RegAddrMin(_) => NatAttrClass::REG_ADDR_MIN,
+            NatAttr::RegAddrMax(_) => NatAttrClass::REG_ADDR_MAX,
+            NatAttr::RegProtoMin(_) => NatAttrClass::REG_PROTO_MIN,
+            NatAttr::RegProtoMax(_) => NatAttrClass::REG_PROTO_MAX,
+            NatAttr::Flags(_) => NatAttrClass::FLAGS,
+        }
+    }
+}
+
+impl Attribute for NatAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            NatAttr::Type(type_) => type_.as_bytes(),
+            NatAttr::Family(family) => family.as_bytes(),
+            NatAttr::RegAddrMin(reg) => reg.as_bytes(),
+            NatAttr::RegAddrMax(reg) => reg.as_bytes(),
+            NatAttr::RegProtoMin(reg) => reg.as_bytes(),
+            NatAttr::RegProtoMax(reg) => reg.as_bytes(),
+            NatAttr::Flags(flags) => flags.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match NatAttrClass::try_from(header.type_()) {
+            Ok(NatAttrClass::TYPE) => Self::Type(reader.read_val()?),
+            Ok(NatAttrClass::FAMILY) => Self::Family(reader.read_val()?),
+            Ok(NatAttrClass::REG_ADDR_MIN) => Self::RegAddrMin(reader.read_val()?),
+            Ok(NatAttrClass::REG_ADDR_MAX) => Self::RegAddrMax(reader.read_val()?),
+            Ok(NatAttrClass::REG_PROTO_MIN) => Self::RegProtoMin(reader.read_val()?),
+            Ok(NatAttrClass::REG_PROTO_MAX) => Self::RegProtoMax(reader.read_val()?),
+            Ok(NatAttrClass::FLAGS) => Self::Flags(reader.read_val()?),
+            class => {
+                debug!("NAT attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Attributes of the `masq` expression.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum MasqAttrClass {
+    UNSPEC = 0,
+    FLAGS = 1,
+    REG_PROTO_MIN = 2,
+    REG_PROTO_MAX = 3,
+}
+
+#[derive(Debug)]
+pub enum MasqAttr {
+    Flags(Be32),
+    RegProtoMin(Be32),
+    RegProtoMax(Be32),
+}
+
+impl MasqAttr {
+    fn class(&self) -> MasqAttrClass {
+        match self {
+            MasqAttr::Flags(_) => MasqAttrClass::FLAGS,
+            MasqAttr::RegProtoMin(_) => MasqAttrClass::REG_PROTO_MIN,
+            MasqAttr::RegProtoMax(_) => MasqAttrClass::REG_PROTO_MAX,
+        }
+    }
+}
+
+impl Attribute for MasqAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            MasqAttr::Flags(flags) => flags.as_bytes(),
+            MasqAttr::RegProtoMin(reg) => reg.as_bytes(),
+            MasqAttr::RegProtoMax(reg) => reg.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match MasqAttrClass::try_from(header.type_()) {
+            Ok(MasqAttrClass::FLAGS) => Self::Flags(reader.read_val()?),
+            Ok(MasqAttrClass::REG_PROTO_MIN) => Self::RegProtoMin(reader.read_val()?),
+            Ok(MasqAttrClass::REG_PROTO_MAX) => Self::RegProtoMax(reader.read_val()?),
+            class => {
+                debug!("masquerade attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::Be32;
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Generation-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum GenAttrClass {
+    UNSPEC = 0,
+    ID = 1,
+    PROC_PID = 2,
+    PROC_NAME = 3,
+}
+
+#[derive(Debug)]
+pub enum GenAttr {
+    Id(Be32),
+    ProcPid(Be32),
+    ProcName(CString),
+}
+
+impl GenAttr {
+    fn class(&self) -> GenAttrClass {
+        match self {
+            GenAttr::Id(_) => GenAttrClass::ID,
+            GenAttr::ProcPid(_) => GenAttrClass::PROC_PID,
+            GenAttr::ProcName(_) => GenAttrClass::PROC_NAME,
+        }
+    }
+}
+
+impl Attribute for GenAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            GenAttr::Id(id) => id.as_bytes(),
+            GenAttr::ProcPid(pid) => pid.as_bytes(),
+            GenAttr::ProcName(name) => name.as_bytes_with_nul(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, _reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        // The generation requests have no meaningful attributes.
+        let class = GenAttrClass::try_from(header.type_());
+        debug!("generation attribute `{:?}` is ignored", class);
+
+        Ok(None)
+    }
+}
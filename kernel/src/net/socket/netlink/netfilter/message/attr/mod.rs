@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    net::{
+        netfilter::MAX_NAME_LEN,
+        socket::netlink::message::{CAttrHeader, Nested},
+    },
+    prelude::*,
+    util::MultiRead,
+};
+
+pub mod chain;
+pub mod expr;
+pub mod generation;
+pub mod rule;
+pub mod table;
+
+/// A big-endian `u32` in an attribute payload.
+///
+/// Unlike other netlink protocols, nf_tables encodes integers in network byte order.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct Be32(u32);
+
+impl Be32 {
+    pub fn new(value: u32) -> Self {
+        Self(value.to_be())
+    }
+
+    pub fn get(self) -> u32 {
+        u32::from_be(self.0)
+    }
+}
+
+/// A big-endian `u64` in an attribute payload.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct Be64(u64);
+
+impl Be64 {
+    pub fn new(value: u64) -> Self {
+        Self(value.to_be())
+    }
+
+    pub fn get(self) -> u64 {
+        u64::from_be(self.0)
+    }
+}
+
+/// Reads the payload of an attribute as bytes.
+fn read_bytes(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; header.payload_len()];
+    reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+    Ok(bytes)
+}
+
+/// Reads the payload of an attribute as a name.
+///
+/// The name must be terminated by the only zero in the payload.
+fn read_name(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<CString> {
+    if header.payload_len() > MAX_NAME_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the name is too long");
+    }
+
+    let bytes = read_bytes(header, reader)?;
+    CString::from_vec_with_nul(bytes)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the name is not terminated properly"))
+}
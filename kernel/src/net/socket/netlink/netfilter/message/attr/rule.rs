@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{read_name, Be64, Nested};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Rule-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum RuleAttrClass {
+    UNSPEC = 0,
+    TABLE = 1,
+    CHAIN = 2,
+    HANDLE = 3,
+    EXPRESSIONS = 4,
+    COMPAT = 5,
+    POSITION = 6,
+    USERDATA = 7,
+    PAD = 8,
+    ID = 9,
+    POSITION_ID = 10,
+    CHAIN_ID = 11,
+}
+
+#[derive(Debug)]
+pub enum RuleAttr {
+    Table(CString),
+    Chain(CString),
+    Handle(Be64),
+    /// The list of expressions (see [`ListAttr`]).
+    Expressions(Nested),
+    /// The handle of the rule before (or after) which the new rule is inserted.
+    Position(Be64),
+}
+
+impl RuleAttr {
+    fn class(&self) -> RuleAttrClass {
+        match self {
+            RuleAttr::Table(_) => RuleAttrClass::TABLE,
+            RuleAttr::Chain(_) => RuleAttrClass::CHAIN,
+            RuleAttr::Handle(_) => RuleAttrClass::HANDLE,
+            RuleAttr::Expressions(_) => RuleAttrClass::EXPRESSIONS,
+            RuleAttr::Position(_) => RuleAttrClass::POSITION,
+        }
+    }
+}
+
+impl Attribute for RuleAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            RuleAttr::Table(table) => table.as_bytes_with_nul(),
+            RuleAttr::Chain(chain) => chain.as_bytes_with_nul(),
+            RuleAttr::Handle(handle) => handle.as_bytes(),
+            RuleAttr::Expressions(exprs) => exprs.as_bytes(),
+            RuleAttr::Position(position) => position.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match RuleAttrClass::try_from(header.type_()) {
+            Ok(RuleAttrClass::TABLE) => Self::Table(read_name(header, reader)?),
+            Ok(RuleAttrClass::CHAIN) => Self::Chain(read_name(header, reader)?),
+            Ok(RuleAttrClass::HANDLE) => Self::Handle(reader.read_val()?),
+            Ok(RuleAttrClass::EXPRESSIONS) => Self::Expressions(Nested::read_from(header, reader)?),
+            Ok(RuleAttrClass::POSITION) => Self::Position(reader.read_val()?),
+            class => {
+                debug!("rule attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// List-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum ListAttrClass {
+    UNSPEC = 0,
+    ELEM = 1,
+}
+
+#[derive(Debug)]
+pub enum ListAttr {
+    /// An element (e.g., the expression attributes in [`ExprAttr`]).
+    Elem(Nested),
+}
+
+impl ListAttr {
+    fn class(&self) -> ListAttrClass {
+        match self {
+            ListAttr::Elem(_) => ListAttrClass::ELEM,
+        }
+    }
+}
+
+impl Attribute for ListAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            ListAttr::Elem(elem) => elem.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match ListAttrClass::try_from(header.type_()) {
+            Ok(ListAttrClass::ELEM) => Self::Elem(Nested::read_from(header, reader)?),
+            class => {
+                debug!("list attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Expression-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum ExprAttrClass {
+    UNSPEC = 0,
+    NAME = 1,
+    DATA = 2,
+}
+
+#[derive(Debug)]
+pub enum ExprAttr {
+    Name(CString),
+    /// The expression-specific attributes, whose types depend on the expression name.
+    Data(Nested),
+}
+
+impl ExprAttr {
+    fn class(&self) -> ExprAttrClass {
+        match self {
+            ExprAttr::Name(_) => ExprAttrClass::NAME,
+            ExprAttr::Data(_) => ExprAttrClass::DATA,
+        }
+    }
+}
+
+impl Attribute for ExprAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            ExprAttr::Name(name) => name.as_bytes_with_nul(),
+            ExprAttr::Data(data) => data.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match ExprAttrClass::try_from(header.type_()) {
+            Ok(ExprAttrClass::NAME) => Self::Name(read_name(header, reader)?),
+            Ok(ExprAttrClass::DATA) => Self::Data(Nested::read_from(header, reader)?),
+            class => {
+                debug!("expression attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{read_name, Be32, Be64};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Table-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum TableAttrClass {
+    UNSPEC = 0,
+    NAME = 1,
+    FLAGS = 2,
+    USE = 3,
+    HANDLE = 4,
+    PAD = 5,
+    USERDATA = 6,
+    OWNER = 7,
+}
+
+#[derive(Debug)]
+pub enum TableAttr {
+    Name(CString),
+    Flags(Be32),
+    /// The number of chains in the table.
+    Use(Be32),
+    Handle(Be64),
+}
+
+bitflags! {
+    /// Table flags.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+    pub struct TableFlags: u32 {
+        const DORMANT = 0x1;
+        const OWNER = 0x2;
+        const PERSIST = 0x4;
+    }
+}
+
+impl TableAttr {
+    fn class(&self) -> TableAttrClass {
+        match self {
+            TableAttr::Name(_) => TableAttrClass::NAME,
+            TableAttr::Flags(_) => TableAttrClass::FLAGS,
+            TableAttr::Use(_) => TableAttrClass::USE,
+            TableAttr::Handle(_) => TableAttrClass::HANDLE,
+        }
+    }
+}
+
+impl Attribute for TableAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            TableAttr::Name(name) => name.as_bytes_with_nul(),
+            TableAttr::Flags(flags) => flags.as_bytes(),
+            TableAttr::Use(use_) => use_.as_bytes(),
+            TableAttr::Handle(handle) => handle.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match TableAttrClass::try_from(header.type_()) {
+            Ok(TableAttrClass::NAME) => Self::Name(read_name(header, reader)?),
+            Ok(TableAttrClass::FLAGS) => Self::Flags(reader.read_val()?),
+            Ok(TableAttrClass::HANDLE) => Self::Handle(reader.read_val()?),
+            class => {
+                debug!("table attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink message types for the netlink netfilter protocol.
+//!
+//! This module defines how to interpret messages sent from user space and how to write
+//! kernel messages back to user space.
+
+mod attr;
+mod segment;
+
+pub(super) use attr::{
+    chain::{ChainAttr, HookAttr},
+    expr::{
+        BitwiseAttr, CmpAttr, CounterAttr, CtAttr, DataAttr, ImmediateAttr, MasqAttr, MetaAttr,
+        NatAttr, PayloadAttr, RejectAttr, VerdictAttr,
+    },
+    generation::GenAttr,
+    rule::{ExprAttr, ListAttr, RuleAttr},
+    table::{TableAttr, TableFlags},
+    Be32, Be64,
+};
+pub(super) use segment::{
+    ChainSegment, GenSegment, NfGenMsgBody, NfnlSegment, NftMsgType, RuleSegment, TableSegment,
+    NFNL_SUBSYS_NFTABLES,
+};
+
+use crate::net::socket::netlink::message::Message;
+pub(super) use crate::net::socket::netlink::message::Nested;
+
+/// A netlink netfilter message.
+pub(super) type NfnlMessage = Message<NfnlSegment>;
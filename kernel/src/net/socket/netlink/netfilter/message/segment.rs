@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines the segments of the netlink netfilter protocol.
+//!
+//! Each segment type consists of the subsystem ID and the message type, and each segment body is
+//! a [`CNfGenMsg`]. Only the nf_tables subsystem is supported.
+
+use align_ext::AlignExt;
+
+use super::attr::{chain::ChainAttr, generation::GenAttr, rule::RuleAttr, table::TableAttr};
+use crate::{
+    net::socket::netlink::message::{
+        CMsgSegHdr, DoneSegment, ErrorSegment, ProtocolSegment, SegmentBody, SegmentCommon,
+        NLMSG_ALIGN,
+    },
+    prelude::*,
+    util::{MultiRead, MultiWrite},
+};
+
+pub type TableSegment = SegmentCommon<NfGenMsgBody, TableAttr>;
+pub type ChainSegment = SegmentCommon<NfGenMsgBody, ChainAttr>;
+pub type RuleSegment = SegmentCommon<NfGenMsgBody, RuleAttr>;
+pub type GenSegment = SegmentCommon<NfGenMsgBody, GenAttr>;
+/// The segment that begins or ends a batch.
+///
+/// The attribute that a batch segment can carry (i.e., `NFNL_BATCH_GENID`) shares the same
+/// type as [`GenAttr::Id`], but it is ignored.
+pub type BatchSegment = SegmentCommon<NfGenMsgBody, GenAttr>;
+
+/// The netlink netfilter segment, which is the basic unit of a netlink netfilter message.
+#[derive(Debug)]
+pub enum NfnlSegment {
+    BatchBegin(BatchSegment),
+    BatchEnd(BatchSegment),
+    NewTable(TableSegment),
+    GetTable(TableSegment),
+    DelTable(TableSegment),
+    NewChain(ChainSegment),
+    GetChain(ChainSegment),
+    DelChain(ChainSegment),
+    NewRule(RuleSegment),
+    GetRule(RuleSegment),
+    DelRule(RuleSegment),
+    NewGen(GenSegment),
+    GetGen(GenSegment),
+    /// A segment whose type is not supported.
+    ///
+    /// The segment is skipped, but it is still answered with an error.
+    Unsupported(CMsgSegHdr),
+    Done(DoneSegment),
+    Error(ErrorSegment),
+}
+
+/// `nfgenmsg` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CNfGenMsg {
+    /// AF_xxx
+    pub family: u8,
+    /// nfnetlink version
+    pub version: u8,
+    /// Resource ID (in network byte order)
+    pub res_id: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NfGenMsgBody {
+    /// The family, which is `NFPROTO_UNSPEC` (i.e., 0) or one of [`NfProto`].
+    ///
+    /// [`NfProto`]: crate::net::netfilter::NfProto
+    pub family: u8,
+    /// The resource ID, which is the subsystem ID in batch segments or the low 16 bits of the
+    /// generation ID in responses.
+    pub res_id: u16,
+}
+
+impl SegmentBody for NfGenMsgBody {
+    type CType = CNfGenMsg;
+}
+
+impl TryFrom<CNfGenMsg> for NfGenMsgBody {
+    type Error = Error;
+
+    fn try_from(value: CNfGenMsg) -> Result<Self> {
+        // Like Linux, the version is not checked.
+        Ok(Self {
+            family: value.family,
+            res_id: u16::from_be(value.res_id),
+        })
+    }
+}
+
+impl From<NfGenMsgBody> for CNfGenMsg {
+    fn from(value: NfGenMsgBody) -> Self {
+        Self {
+            family: value.family,
+            version: NFNETLINK_V0,
+            res_id: value.res_id.to_be(),
+        }
+    }
+}
+
+const NFNETLINK_V0: u8 = 0;
+
+/// The ID of the nf_tables subsystem.
+pub const NFNL_SUBSYS_NFTABLES: u16 = 10;
+
+/// The segment types that are specific to the netlink netfilter protocol.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nfnetlink.h>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+enum NfnlSegmentType {
+    BATCH_BEGIN = 0x10,
+    BATCH_END = 0x11,
+}
+
+/// The message types of the nf_tables subsystem.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/netfilter/nf_tables.h>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub enum NftMsgType {
+    NEWTABLE = 0,
+    GETTABLE = 1,
+    DELTABLE = 2,
+    NEWCHAIN = 3,
+    GETCHAIN = 4,
+    DELCHAIN = 5,
+    NEWRULE = 6,
+    GETRULE = 7,
+    DELRULE = 8,
+    NEWGEN = 15,
+    GETGEN = 16,
+}
+
+impl NftMsgType {
+    /// Returns the segment type of the message type.
+    pub fn segment_type(self) -> u16 {
+        (NFNL_SUBSYS_NFTABLES << 8) | self as u16
+    }
+}
+
+impl ProtocolSegment for NfnlSegment {
+    fn header(&self) -> &CMsgSegHdr {
+        match self {
+            NfnlSegment::BatchBegin(batch_segment) | NfnlSegment::BatchEnd(batch_segment) => {
+                batch_segment.header()
+            }
+            NfnlSegment::NewTable(table_segment)
+            | NfnlSegment::GetTable(table_segment)
+            | NfnlSegment::DelTable(table_segment) => table_segment.header(),
+            NfnlSegment::NewChain(chain_segment)
+            | NfnlSegment::GetChain(chain_segment)
+            | NfnlSegment::DelChain(chain_segment) => chain_segment.header(),
+            NfnlSegment::NewRule(rule_segment)
+            | NfnlSegment::GetRule(rule_segment)
+            | NfnlSegment::DelRule(rule_segment) => rule_segment.header(),
+            NfnlSegment::NewGen(gen_segment) | NfnlSegment::GetGen(gen_segment) => {
+                gen_segment.header()
+            }
+            NfnlSegment::Unsupported(header) => header,
+            NfnlSegment::Done(done_segment) => done_segment.header(),
+            NfnlSegment::Error(error_segment) => error_segment.header(),
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut CMsgSegHdr {
+        match self {
+            NfnlSegment::BatchBegin(batch_segment) | NfnlSegment::BatchEnd(batch_segment) => {
+                batch_segment.header_mut()
+            }
+            NfnlSegment::NewTable(table_segment)
+            | NfnlSegment::GetTable(table_segment)
+            | NfnlSegment::DelTable(table_segment) => table_segment.header_mut(),
+            NfnlSegment::NewChain(chain_segment)
+            | NfnlSegment::GetChain(chain_segment)
+            | NfnlSegment::DelChain(chain_segment) => chain_segment.header_mut(),
+            NfnlSegment::NewRule(rule_segment)
+            | NfnlSegment::GetRule(rule_segment)
+            | NfnlSegment::DelRule(rule_segment) => rule_segment.header_mut(),
+            NfnlSegment::NewGen(gen_segment) | NfnlSegment::GetGen(gen_segment) => {
+                gen_segment.header_mut()
+            }
+            NfnlSegment::Unsupported(header) => header,
+            NfnlSegment::Done(done_segment) => done_segment.header_mut(),
+            NfnlSegment::Error(error_segment) => error_segment.header_mut(),
+        }
+    }
+
+    fn read_from(reader: &mut dyn MultiRead) -> Result<Self> {
+        let header = reader.read_val::<CMsgSegHdr>()?;
+
+        if let Ok(segment_type) = NfnlSegmentType::try_from(header.type_) {
+            let segment = BatchSegment::read_from(header, reader)?;
+            return Ok(match segment_type {
+                NfnlSegmentType::BATCH_BEGIN => NfnlSegment::BatchBegin(segment),
+                NfnlSegmentType::BATCH_END => NfnlSegment::BatchEnd(segment),
+            });
+        }
+
+        let msg_type = if header.type_ >> 8 == NFNL_SUBSYS_NFTABLES {
+            NftMsgType::try_from((header.type_ & 0xff) as u8).ok()
+        } else {
+            None
+        };
+        let Some(msg_type) = msg_type else {
+            // Skip the rest of the segment, including the padding bytes.
+            let skipped_len = (header.len as usize)
+                .checked_sub(size_of::<CMsgSegHdr>())
+                .ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "the message length is too small")
+                })?
+                .align_up(NLMSG_ALIGN)
+                .min(reader.sum_lens());
+            reader.skip(skipped_len);
+            return Ok(NfnlSegment::Unsupported(header));
+        };
+
+        let segment = match msg_type {
+            NftMsgType::NEWTABLE => NfnlSegment::NewTable(TableSegment::read_from(header, reader)?),
+            NftMsgType::GETTABLE => NfnlSegment::GetTable(TableSegment::read_from(header, reader)?),
+            NftMsgType::DELTABLE => NfnlSegment::DelTable(TableSegment::read_from(header, reader)?),
+            NftMsgType::NEWCHAIN => NfnlSegment::NewChain(ChainSegment::read_from(header, reader)?),
+            NftMsgType::GETCHAIN => NfnlSegment::GetChain(ChainSegment::read_from(header, reader)?),
+            NftMsgType::DELCHAIN => NfnlSegment::DelChain(ChainSegment::read_from(header, reader)?),
+            NftMsgType::NEWRULE => NfnlSegment::NewRule(RuleSegment::read_from(header, reader)?),
+            NftMsgType::GETRULE => NfnlSegment::GetRule(RuleSegment::read_from(header, reader)?),
+            NftMsgType::DELRULE => NfnlSegment::DelRule(RuleSegment::read_from(header, reader)?),
+            NftMsgType::NEWGEN => NfnlSegment::NewGen(GenSegment::read_from(header, reader)?),
+            NftMsgType::GETGEN => NfnlSegment::GetGen(GenSegment::read_from(header, reader)?),
+        };
+
+        Ok(segment)
+    }
+
+    fn write_to(&self, writer: &mut dyn MultiWrite) -> Result<()> {
+        match self {
+            NfnlSegment::NewTable(table_segment) => table_segment.write_to(writer)?,
+            NfnlSegment::NewChain(chain_segment) => chain_segment.write_to(writer)?,
+            NfnlSegment::NewRule(rule_segment) => rule_segment.write_to(writer)?,
+            NfnlSegment::NewGen(gen_segment) => gen_segment.write_to(writer)?,
+            NfnlSegment::Done(done_segment) => done_segment.write_to(writer)?,
+            NfnlSegment::Error(error_segment) => error_segment.write_to(writer)?,
+            NfnlSegment::GetTable(_)
+            | NfnlSegment::GetChain(_)
+            | NfnlSegment::GetRule(_)
+            | NfnlSegment::GetGen(_) => {
+                unreachable!("kernel should not write get requests to user space");
+            }
+            NfnlSegment::DelTable(_) | NfnlSegment::DelChain(_) | NfnlSegment::DelRule(_) => {
+                unreachable!("kernel should not write delete requests to user space");
+            }
+            NfnlSegment::BatchBegin(_) | NfnlSegment::BatchEnd(_) | NfnlSegment::Unsupported(_) => {
+                unreachable!("kernel should not write batch or unsupported segments to user space");
+            }
+        }
+        Ok(())
+    }
+}
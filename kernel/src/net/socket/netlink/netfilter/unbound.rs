@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::bound::BoundNetlinkNetfilter;
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        netlink::{table::NETLINK_SOCKET_TABLE, NetlinkSocketAddr, StandardNetlinkProtocol},
+        util::datagram_common,
+    },
+    prelude::*,
+    process::signal::Pollee,
+};
+
+pub(super) struct UnboundNetlinkNetfilter {
+    _private: (),
+}
+
+impl UnboundNetlinkNetfilter {
+    pub(super) const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl datagram_common::Unbound for UnboundNetlinkNetfilter {
+    type Endpoint = NetlinkSocketAddr;
+    type BindOptions = ();
+
+    type Bound = BoundNetlinkNetfilter;
+
+    fn bind(
+        &mut self,
+        endpoint: &Self::Endpoint,
+        _pollee: &Pollee,
+        _options: Self::BindOptions,
+    ) -> Result<BoundNetlinkNetfilter> {
+        let bound_handle =
+            NETLINK_SOCKET_TABLE.bind(StandardNetlinkProtocol::NETFILTER as _, endpoint)?;
+
+        Ok(BoundNetlinkNetfilter::new(bound_handle))
+    }
+
+    fn bind_ephemeral(
+        &mut self,
+        _remote_endpoint: &Self::Endpoint,
+        _pollee: &Pollee,
+    ) -> Result<Self::Bound> {
+        let bound_handle = NETLINK_SOCKET_TABLE.bind(
+            StandardNetlinkProtocol::NETFILTER as _,
+            &NetlinkSocketAddr::new_unspecified(),
+        )?;
+
+        Ok(BoundNetlinkNetfilter::new(bound_handle))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        IoEvents::OUT
+    }
+}
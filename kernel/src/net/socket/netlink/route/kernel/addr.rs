@@ -4,14 +4,19 @@
 
 use core::num::NonZeroU32;
 
-use super::util::finish_response;
+use aster_bigtcp::wire::IpCidr;
+
+use super::util::{check_net_admin, finish_response, FamilyFilter};
 use crate::{
     net::{
         iface::{iter_all_ifaces, Iface},
         socket::netlink::{
-            message::{CMsgSegHdr, CSegmentType, GetRequestFlags, SegHdrCommonFlags},
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
             route::message::{
-                AddrAttr, AddrMessageFlags, AddrSegment, AddrSegmentBody, RtScope, RtnlSegment,
+                AddrAttr, AddrMessageFlags, AddrSegment, AddrSegmentBody, IpAddrBytes, RtScope,
+                RtnlSegment,
             },
         },
     },
@@ -28,9 +33,11 @@ pub(super) fn do_get_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegme
         return_errno_with_message!(Errno::EOPNOTSUPP, "GETADDR only supports dump requests");
     }
 
+    let family_filter = FamilyFilter::from_family(request_segment.body().family);
+
     let mut response_segments: Vec<RtnlSegment> = iter_all_ifaces()
         // GETADDR only supports dump mode, so we're going to report all addresses.
-        .filter_map(|iface| iface_to_new_addr(request_segment.header(), iface))
+        .flat_map(|iface| iface_to_new_addrs(request_segment.header(), &iface, family_filter))
         .map(RtnlSegment::NewAddr)
         .collect();
 
@@ -39,9 +46,163 @@ pub(super) fn do_get_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegme
     Ok(response_segments)
 }
 
-fn iface_to_new_addr(request_header: &CMsgSegHdr, iface: &Arc<Iface>) -> Option<AddrSegment> {
-    let ipv4_addr = iface.ipv4_addr()?;
+pub(super) fn do_new_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let iface = find_iface(request_segment)?;
+    let ip_cidr = parse_ip_cidr(request_segment)?;
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    if iface_has_addr(&iface, &ip_cidr) {
+        if flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the address already exists");
+        }
+        return Ok(Vec::new());
+    }
+
+    let is_added = match ip_cidr {
+        IpCidr::Ipv4(ipv4_cidr) => iface.add_ipv4_addr(ipv4_cidr),
+        IpCidr::Ipv6(ipv6_cidr) => iface.add_ipv6_addr(ipv6_cidr),
+    };
+    if !is_added {
+        return_errno_with_message!(Errno::ENOSPC, "the interface has too many addresses");
+    }
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_del_addr(request_segment: &AddrSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let iface = find_iface(request_segment)?;
+    let ip_cidr = parse_ip_cidr(request_segment)?;
+
+    if !iface.remove_ip_addr(ip_cidr) {
+        return_errno_with_message!(Errno::EADDRNOTAVAIL, "the address does not exist");
+    }
+
+    Ok(Vec::new())
+}
+
+fn find_iface(request_segment: &AddrSegment) -> Result<Arc<Iface>> {
+    let Some(index) = request_segment.body().index else {
+        return_errno_with_message!(Errno::ENODEV, "the interface index is not specified");
+    };
+
+    iter_all_ifaces()
+        .find(|iface| iface.index() == index.get())
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the interface does not exist"))
+}
+
+/// Parses the address to add or delete.
+///
+/// Like Linux, the local address takes precedence over the (peer) address.
+fn parse_ip_cidr(request_segment: &AddrSegment) -> Result<IpCidr> {
+    let attrs = request_segment.attrs();
+    let addr = attrs
+        .iter()
+        .find_map(|attr| match attr {
+            AddrAttr::Local(addr) => Some(*addr),
+            _ => None,
+        })
+        .or_else(|| {
+            attrs.iter().find_map(|attr| match attr {
+                AddrAttr::Address(addr) => Some(*addr),
+                _ => None,
+            })
+        });
+    let Some(addr) = addr else {
+        return_errno_with_message!(Errno::EINVAL, "the address is not specified");
+    };
+
+    let body = request_segment.body();
+    let ip_cidr = match (CSocketAddrFamily::try_from(body.family), addr) {
+        (Ok(CSocketAddrFamily::AF_INET), IpAddrBytes::V4(_)) if body.prefix_len <= 32 => {
+            IpCidr::new(addr.into(), body.prefix_len)
+        }
+        (Ok(CSocketAddrFamily::AF_INET6), IpAddrBytes::V6(_)) if body.prefix_len <= 128 => {
+            IpCidr::new(addr.into(), body.prefix_len)
+        }
+        (Ok(CSocketAddrFamily::AF_INET | CSocketAddrFamily::AF_INET6), _) => {
+            return_errno_with_message!(Errno::EINVAL, "the address or the prefix is invalid")
+        }
+        _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "the family is not supported"),
+    };
+
+    Ok(ip_cidr)
+}
 
+fn iface_has_addr(iface: &Iface, ip_cidr: &IpCidr) -> bool {
+    match ip_cidr {
+        IpCidr::Ipv4(ipv4_cidr) => iface.ipv4_addrs().contains(ipv4_cidr),
+        IpCidr::Ipv6(ipv6_cidr) => iface.ipv6_addrs().contains(ipv6_cidr),
+    }
+}
+
+fn iface_to_new_addrs(
+    request_header: &CMsgSegHdr,
+    iface: &Arc<Iface>,
+    family_filter: FamilyFilter,
+) -> Vec<AddrSegment> {
+    let mut segments = Vec::new();
+
+    if family_filter.contains_ipv4() {
+        for ipv4_cidr in iface.ipv4_addrs() {
+            let scope = if ipv4_cidr.address().is_loopback() {
+                RtScope::HOST
+            } else {
+                RtScope::UNIVERSE
+            };
+            let attrs = vec![
+                AddrAttr::Address(IpAddrBytes::V4(ipv4_cidr.address().octets())),
+                AddrAttr::Label(CString::new(iface.name()).unwrap()),
+                AddrAttr::Local(IpAddrBytes::V4(ipv4_cidr.address().octets())),
+            ];
+            segments.push(new_addr_segment(
+                request_header,
+                iface,
+                CSocketAddrFamily::AF_INET,
+                ipv4_cidr.prefix_len(),
+                scope,
+                attrs,
+            ));
+        }
+    }
+
+    if family_filter.contains_ipv6() {
+        for ipv6_cidr in iface.ipv6_addrs() {
+            let addr = ipv6_cidr.address();
+            let scope = if addr.is_loopback() {
+                RtScope::HOST
+            } else if addr.is_unicast_link_local() {
+                RtScope::LINK
+            } else {
+                RtScope::UNIVERSE
+            };
+            // Like Linux, IPv6 addresses have no labels or local addresses.
+            let attrs = vec![AddrAttr::Address(IpAddrBytes::V6(addr.octets()))];
+            segments.push(new_addr_segment(
+                request_header,
+                iface,
+                CSocketAddrFamily::AF_INET6,
+                ipv6_cidr.prefix_len(),
+                scope,
+                attrs,
+            ));
+        }
+    }
+
+    segments
+}
+
+fn new_addr_segment(
+    request_header: &CMsgSegHdr,
+    iface: &Arc<Iface>,
+    family: CSocketAddrFamily,
+    prefix_len: u8,
+    scope: RtScope,
+    attrs: Vec<AddrAttr>,
+) -> AddrSegment {
     let header = CMsgSegHdr {
         len: 0,
         type_: CSegmentType::NEWADDR as _,
@@ -51,18 +212,12 @@ fn iface_to_new_addr(request_header: &CMsgSegHdr, iface: &Arc<Iface>) -> Option<
     };
 
     let addr_message = AddrSegmentBody {
-        family: CSocketAddrFamily::AF_INET as _,
-        prefix_len: iface.prefix_len().unwrap(),
+        family: family as _,
+        prefix_len,
         flags: AddrMessageFlags::PERMANENT,
-        scope: RtScope::HOST,
+        scope,
         index: NonZeroU32::new(iface.index()),
     };
 
-    let attrs = vec![
-        AddrAttr::Address(ipv4_addr.octets()),
-        AddrAttr::Label(CString::new(iface.name()).unwrap()),
-        AddrAttr::Local(ipv4_addr.octets()),
-    ];
-
-    Some(AddrSegment::new(header, addr_message, attrs))
+    AddrSegment::new(header, addr_message, attrs)
 }
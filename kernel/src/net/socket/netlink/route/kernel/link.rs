@@ -2,17 +2,30 @@
 
 //! Handle link-related requests.
 
-use core::num::NonZero;
+use core::{
+    num::{NonZero, NonZeroU32},
+    time::Duration,
+};
 
 use aster_bigtcp::iface::InterfaceType;
 
-use super::util::finish_response;
+use super::util::{check_net_admin, finish_response};
 use crate::{
     net::{
-        iface::{iter_all_ifaces, Iface},
+        iface::{
+            alloc_iface_name, delete_bridge, delete_tun, delete_wireguard, find_bridge,
+            find_master, find_wireguard, is_tun, iter_all_ifaces, new_bridge, new_wireguard,
+            set_master, Bridge, Iface,
+        },
         socket::netlink::{
-            message::{CMsgSegHdr, CSegmentType, GetRequestFlags, SegHdrCommonFlags},
-            route::message::{LinkAttr, LinkSegment, LinkSegmentBody, RtnlSegment},
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, Nested, NewRequestFlags,
+                SegHdrCommonFlags,
+            },
+            route::message::{
+                BridgeAttr, BridgePortAttr, LinkAttr, LinkInfoAttr, LinkSegment, LinkSegmentBody,
+                RtnlSegment,
+            },
         },
     },
     prelude::*,
@@ -29,7 +42,7 @@ pub(super) fn do_get_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegme
             FilterBy::Name(name) => *name == iface.name(),
             FilterBy::Dump => true,
         })
-        .map(|iface| iface_to_new_link(request_segment.header(), iface))
+        .map(|iface| iface_to_new_link(request_segment.header(), &iface))
         .map(RtnlSegment::NewLink)
         .collect();
 
@@ -44,6 +57,279 @@ pub(super) fn do_get_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegme
     Ok(response_segments)
 }
 
+pub(super) fn do_new_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = LinkRequest::parse(request_segment)?;
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    if let Some(iface) = request.find_iface() {
+        if flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the link already exists");
+        }
+        if flags.contains(NewRequestFlags::REPLACE) {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "links cannot be replaced");
+        }
+        change_link(&iface, &request)?;
+        return Ok(Vec::new());
+    }
+
+    if !flags.contains(NewRequestFlags::CREATE) {
+        return_errno_with_message!(Errno::ENODEV, "the link does not exist");
+    }
+    if request.index.is_some() {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "specifying the index of a new link is not supported"
+        );
+    }
+    match request.kind.as_deref() {
+        Some(BRIDGE_KIND) => (),
+        Some(WIREGUARD_KIND) => {
+            let name = match request.name {
+                Some(name) if name.contains("%d") => alloc_iface_name(&name)?,
+                Some(name) => name,
+                None => alloc_iface_name(WIREGUARD_NAME_TEMPLATE)?,
+            };
+            new_wireguard(&name)?;
+            return Ok(Vec::new());
+        }
+        Some(_) => return_errno_with_message!(Errno::EOPNOTSUPP, "the link kind is not supported"),
+        None => return_errno_with_message!(Errno::EOPNOTSUPP, "the link kind is not specified"),
+    }
+
+    let bridge_attrs = request.data.as_ref().map(parse_bridge_attrs).transpose()?;
+
+    let name = match request.name {
+        Some(name) if name.contains("%d") => alloc_iface_name(&name)?,
+        Some(name) => name,
+        None => alloc_iface_name(BRIDGE_NAME_TEMPLATE)?,
+    };
+    let bridge = new_bridge(&name)?;
+    if let Some(bridge_attrs) = bridge_attrs {
+        apply_bridge_attrs(&bridge, &bridge_attrs);
+    }
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_set_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = LinkRequest::parse(request_segment)?;
+    let Some(iface) = request.find_iface() else {
+        return_errno_with_message!(Errno::ENODEV, "the link does not exist");
+    };
+
+    // Like Linux, the link info is ignored in SETLINK requests.
+    if let Some(master) = request.master {
+        set_master(&iface, master)?;
+    }
+
+    Ok(Vec::new())
+}
+
+pub(super) fn do_del_link(request_segment: &LinkSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = LinkRequest::parse(request_segment)?;
+    let Some(iface) = request.find_iface() else {
+        return_errno_with_message!(Errno::ENODEV, "the link does not exist");
+    };
+
+    if is_tun(iface.index()) {
+        delete_tun(iface.index())?;
+    } else if find_wireguard(iface.index()).is_some() {
+        delete_wireguard(iface.index())?;
+    } else {
+        delete_bridge(iface.index())?;
+    }
+
+    Ok(Vec::new())
+}
+
+/// The link kind of bridges.
+const BRIDGE_KIND: &str = "bridge";
+/// The link kind of TUN/TAP devices.
+const TUN_KIND: &str = "tun";
+/// The link kind of WireGuard devices.
+const WIREGUARD_KIND: &str = "wireguard";
+
+/// The template of the names of bridges.
+const BRIDGE_NAME_TEMPLATE: &str = "bridge%d";
+/// The template of the names of WireGuard devices.
+const WIREGUARD_NAME_TEMPLATE: &str = "wg%d";
+
+/// A request to create, change, or delete a link.
+struct LinkRequest {
+    index: Option<NonZeroU32>,
+    name: Option<String>,
+    master: Option<u32>,
+    kind: Option<String>,
+    data: Option<Nested>,
+    slave_kind: Option<String>,
+    slave_data: Option<Nested>,
+}
+
+impl LinkRequest {
+    fn parse(request_segment: &LinkSegment) -> Result<Self> {
+        let mut request = Self {
+            index: request_segment.body().index,
+            name: None,
+            master: None,
+            kind: None,
+            data: None,
+            slave_kind: None,
+            slave_data: None,
+        };
+
+        for attr in request_segment.attrs() {
+            match attr {
+                LinkAttr::Name(name) => request.name = Some(cstr_to_string(name)?),
+                LinkAttr::Master(master) => request.master = Some(*master),
+                LinkAttr::LinkInfo(link_info) => request.parse_link_info(link_info)?,
+                _ => (),
+            }
+        }
+
+        Ok(request)
+    }
+
+    fn parse_link_info(&mut self, link_info: &Nested) -> Result<()> {
+        for attr in link_info.parse::<LinkInfoAttr>()? {
+            match attr {
+                LinkInfoAttr::Kind(kind) => self.kind = Some(cstr_to_string(&kind)?),
+                LinkInfoAttr::Data(data) => self.data = Some(data),
+                LinkInfoAttr::SlaveKind(kind) => self.slave_kind = Some(cstr_to_string(&kind)?),
+                LinkInfoAttr::SlaveData(data) => self.slave_data = Some(data),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the iface to operate on.
+    ///
+    /// Like [`FilterBy::from_request`], `index` takes precedence over `name`.
+    fn find_iface(&self) -> Option<Arc<Iface>> {
+        if let Some(index) = self.index {
+            return iter_all_ifaces().find(|iface| iface.index() == index.get());
+        }
+
+        let name = self.name.as_deref()?;
+        iter_all_ifaces().find(|iface| iface.name() == name)
+    }
+}
+
+fn cstr_to_string(cstr: &CStr) -> Result<String> {
+    cstr.to_str()
+        .map(ToString::to_string)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the string is not valid UTF-8"))
+}
+
+/// Changes an existing link.
+///
+/// The changes are applied in the same order as Linux.
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/rtnetlink.c#L3733>.
+fn change_link(iface: &Arc<Iface>, request: &LinkRequest) -> Result<()> {
+    let bridge = find_bridge(iface.index());
+
+    if let Some(kind) = request.kind.as_deref() {
+        if kind != BRIDGE_KIND || bridge.is_none() {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the link kind cannot be changed");
+        }
+    }
+
+    if let Some(data) = request.data.as_ref() {
+        let Some(bridge) = bridge.as_ref().filter(|_| request.kind.is_some()) else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the link data cannot be changed");
+        };
+        let bridge_attrs = parse_bridge_attrs(data)?;
+        apply_bridge_attrs(bridge, &bridge_attrs);
+    }
+
+    if let Some(slave_data) = request.slave_data.as_ref() {
+        let master = find_master(iface.index());
+        let Some((_, master)) =
+            master.filter(|_| request.slave_kind.as_deref() == Some(BRIDGE_KIND))
+        else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the slave data cannot be changed");
+        };
+        let port_attrs = slave_data.parse::<BridgePortAttr>()?;
+        apply_bridge_port_attrs(&master, iface.index(), &port_attrs)?;
+    }
+
+    if let Some(master) = request.master {
+        set_master(iface, master)?;
+    }
+
+    Ok(())
+}
+
+/// The number of centiseconds (i.e., the `clock_t` unit) in a millisecond.
+const MSECS_PER_CLOCK_T: u64 = 10;
+
+fn parse_bridge_attrs(data: &Nested) -> Result<Vec<BridgeAttr>> {
+    let attrs = data.parse::<BridgeAttr>()?;
+
+    // Check the attributes before applying any of them.
+    for attr in attrs.iter() {
+        if let BridgeAttr::StpState(stp_state) = attr {
+            if *stp_state != 0 {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "STP is not supported");
+            }
+        }
+    }
+
+    Ok(attrs)
+}
+
+fn apply_bridge_attrs(bridge: &Bridge, attrs: &[BridgeAttr]) {
+    for attr in attrs.iter() {
+        match attr {
+            BridgeAttr::AgeingTime(ageing_time) => bridge.set_ageing_time(Duration::from_millis(
+                *ageing_time as u64 * MSECS_PER_CLOCK_T,
+            )),
+            BridgeAttr::StpState(_) => (),
+        }
+    }
+}
+
+/// The port states that are reported to and configured by user space.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_bridge.h#L46>.
+const BR_STATE_DISABLED: u8 = 0;
+const BR_STATE_FORWARDING: u8 = 3;
+
+fn apply_bridge_port_attrs(
+    bridge: &Bridge,
+    port_index: u32,
+    attrs: &[BridgePortAttr],
+) -> Result<()> {
+    // Check the attributes before applying any of them.
+    for attr in attrs.iter() {
+        if let BridgePortAttr::State(state) = attr {
+            if *state != BR_STATE_DISABLED && *state != BR_STATE_FORWARDING {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "only the disabled and forwarding states are supported"
+                );
+            }
+        }
+    }
+
+    for attr in attrs.iter() {
+        match attr {
+            BridgePortAttr::State(state) => {
+                bridge.set_port_blocked(port_index, *state == BR_STATE_DISABLED)?
+            }
+            BridgePortAttr::Guard(guard) => bridge.set_port_guard(port_index, *guard != 0)?,
+        }
+    }
+
+    Ok(())
+}
+
 enum FilterBy<'a> {
     Index(u32),
     Name(&'a str),
@@ -136,10 +422,52 @@ fn iface_to_new_link(request_header: &CMsgSegHdr, iface: &Arc<Iface>) -> LinkSeg
         flags: iface.flags(),
     };
 
-    let attrs = vec![
+    let mut attrs = vec![
         LinkAttr::Name(CString::new(iface.name()).unwrap()),
         LinkAttr::Mtu(iface.mtu() as u32),
     ];
 
+    if is_tun(iface.index()) {
+        let link_info_attrs = [LinkInfoAttr::Kind(CString::new(TUN_KIND).unwrap())];
+        attrs.push(LinkAttr::LinkInfo(Nested::new(&link_info_attrs)));
+    }
+
+    if find_wireguard(iface.index()).is_some() {
+        let link_info_attrs = [LinkInfoAttr::Kind(CString::new(WIREGUARD_KIND).unwrap())];
+        attrs.push(LinkAttr::LinkInfo(Nested::new(&link_info_attrs)));
+    }
+
+    if let Some(bridge) = find_bridge(iface.index()) {
+        let ageing_time = bridge.ageing_time().as_millis() as u64 / MSECS_PER_CLOCK_T;
+        let bridge_attrs = [
+            BridgeAttr::AgeingTime(ageing_time as u32),
+            BridgeAttr::StpState(0),
+        ];
+        let link_info_attrs = [
+            LinkInfoAttr::Kind(CString::new(BRIDGE_KIND).unwrap()),
+            LinkInfoAttr::Data(Nested::new(&bridge_attrs)),
+        ];
+        attrs.push(LinkAttr::LinkInfo(Nested::new(&link_info_attrs)));
+    }
+
+    if let Some((master_index, bridge)) = find_master(iface.index()) {
+        let state = if bridge.is_port_blocked(iface.index()).unwrap_or(true) {
+            BR_STATE_DISABLED
+        } else {
+            BR_STATE_FORWARDING
+        };
+        let guard = bridge.port_guard(iface.index()).unwrap_or(false);
+        let port_attrs = [
+            BridgePortAttr::State(state),
+            BridgePortAttr::Guard(guard as u8),
+        ];
+        let link_info_attrs = [
+            LinkInfoAttr::SlaveKind(CString::new(BRIDGE_KIND).unwrap()),
+            LinkInfoAttr::SlaveData(Nested::new(&port_attrs)),
+        ];
+        attrs.push(LinkAttr::Master(master_index));
+        attrs.push(LinkAttr::LinkInfo(Nested::new(&link_info_attrs)));
+    }
+
     LinkSegment::new(header, link_message, attrs)
 }
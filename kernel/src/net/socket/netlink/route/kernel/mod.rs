@@ -7,12 +7,16 @@ use core::marker::PhantomData;
 
 use super::message::{RtnlMessage, RtnlSegment};
 use crate::{
-    net::socket::netlink::message::{CSegmentType, ErrorSegment, ProtocolSegment},
+    net::socket::netlink::message::{
+        CSegmentType, ErrorSegment, ProtocolSegment, SegHdrCommonFlags,
+    },
     prelude::*,
 };
 
 mod addr;
 mod link;
+mod route;
+mod rule;
 mod util;
 
 pub(super) struct NetlinkRouteKernelSocket {
@@ -39,8 +43,19 @@ impl NetlinkRouteKernelSocket {
             let segment_type = CSegmentType::try_from(request_header.type_).unwrap();
 
             let response_segments = match segment {
+                RtnlSegment::NewLink(request_segment) => link::do_new_link(request_segment),
+                RtnlSegment::DelLink(request_segment) => link::do_del_link(request_segment),
                 RtnlSegment::GetLink(request_segment) => link::do_get_link(request_segment),
+                RtnlSegment::SetLink(request_segment) => link::do_set_link(request_segment),
                 RtnlSegment::GetAddr(request_segment) => addr::do_get_addr(request_segment),
+                RtnlSegment::NewAddr(request_segment) => addr::do_new_addr(request_segment),
+                RtnlSegment::DelAddr(request_segment) => addr::do_del_addr(request_segment),
+                RtnlSegment::GetRoute(request_segment) => route::do_get_route(request_segment),
+                RtnlSegment::NewRoute(request_segment) => route::do_new_route(request_segment),
+                RtnlSegment::DelRoute(request_segment) => route::do_del_route(request_segment),
+                RtnlSegment::GetRule(request_segment) => rule::do_get_rule(request_segment),
+                RtnlSegment::NewRule(request_segment) => rule::do_new_rule(request_segment),
+                RtnlSegment::DelRule(request_segment) => rule::do_del_rule(request_segment),
                 _ => {
                     // FIXME: The error is currently silently ignored.
                     warn!("unsupported request type: {:?}", segment_type);
@@ -49,11 +64,19 @@ impl NetlinkRouteKernelSocket {
             };
 
             let response = match response_segments {
+                // Requests that modify the kernel state have no response segments. Their results
+                // are reported only if the `ACK` flag is set.
+                // Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html#netlink-message-types>.
+                Ok(segments) if segments.is_empty() => {
+                    let flags = SegHdrCommonFlags::from_bits_truncate(request_header.flags);
+                    if !flags.contains(SegHdrCommonFlags::ACK) {
+                        continue;
+                    }
+                    let ack_segment = ErrorSegment::new_from_request(request_header, None);
+                    RtnlMessage::new(vec![RtnlSegment::Error(ack_segment)])
+                }
                 Ok(segments) => RtnlMessage::new(segments),
                 Err(error) => {
-                    // TODO: Deal with the `NetlinkMessageCommonFlags::ACK` flag.
-                    // Should we return `ErrorSegment` if ACK flag does not exist?
-                    // Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html#netlink-message-types>.
                     let err_segment = ErrorSegment::new_from_request(request_header, Some(error));
                     RtnlMessage::new(vec![RtnlSegment::Error(err_segment)])
                 }
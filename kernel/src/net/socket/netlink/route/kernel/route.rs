@@ -0,0 +1,380 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle route-related requests.
+
+use aster_bigtcp::wire::{IpAddress, IpCidr};
+
+use super::util::{
+    check_net_admin, family_of, finish_response, is_included, parse_prefix, unspecified_addr_of,
+    FamilyFilter,
+};
+use crate::{
+    net::{
+        iface::iter_all_ifaces,
+        route::{
+            self, iface_has_addr, iface_subnets, max_prefix_len, FibRoute, RouteResult, RouteType,
+            IPV6_DEFAULT_METRIC, TABLE_LOCAL, TABLE_MAIN,
+        },
+        socket::netlink::{
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
+            route::message::{
+                IpAddrBytes, RouteAttr, RouteSegment, RouteSegmentBody, RtProtocol, RtScope,
+                RtTable, RtType, RtnlSegment,
+            },
+        },
+    },
+    prelude::*,
+};
+
+pub(super) fn do_get_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    let dump_all = {
+        let flags = GetRequestFlags::from_bits_truncate(request_segment.header().flags);
+        flags.contains(GetRequestFlags::DUMP)
+    };
+    if !dump_all {
+        return get_one_route(request_segment);
+    }
+
+    let family_filter = FamilyFilter::from_family(request_segment.body().family);
+
+    let mut response_segments: Vec<RtnlSegment> = route::with_fib(|fib| {
+        let mut table_ids = vec![TABLE_LOCAL, TABLE_MAIN];
+        table_ids.extend(fib.table_ids());
+        table_ids.sort_unstable();
+        table_ids.dedup();
+
+        table_ids
+            .into_iter()
+            .flat_map(|table| {
+                fib.all_routes(table)
+                    .into_iter()
+                    .map(move |route| (table, route))
+            })
+            .filter(|(_, route)| is_included(family_filter, &route.dst))
+            .map(|(table, route)| new_route_segment(request_segment.header(), table, &route))
+            .map(RtnlSegment::NewRoute)
+            .collect()
+    });
+
+    finish_response(request_segment.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+/// Looks up the route to a single destination, which is requested by commands like
+/// `ip route get`.
+fn get_one_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    let unspecified = unspecified_addr_of(request_segment.body().family)?;
+
+    let mut dst = None;
+    let mut src = None;
+    for attr in request_segment.attrs() {
+        match attr {
+            RouteAttr::Dst(addr) => dst = Some(IpAddress::from(*addr)),
+            RouteAttr::Src(addr) => src = Some(IpAddress::from(*addr)),
+            _ => (),
+        }
+    }
+
+    let dst = dst.unwrap_or(unspecified);
+    if dst.version() != unspecified.version()
+        || src.is_some_and(|addr| addr.version() != unspecified.version())
+    {
+        return_errno_with_message!(Errno::EINVAL, "the destination or the source is invalid");
+    }
+
+    let RouteResult {
+        mut route,
+        table,
+        iface,
+    } = route::lookup(&dst, src.as_ref())?;
+
+    // Like Linux, the response describes the route to the single address, along with the source
+    // address that will be used.
+    route.dst = IpCidr::new(dst, max_prefix_len(&dst));
+    route.pref_src = src.or(route.pref_src).or_else(|| match dst {
+        IpAddress::Ipv4(_) => iface.ipv4_addr().map(IpAddress::Ipv4),
+        IpAddress::Ipv6(ipv6_addr) => iface.ipv6_src_addr(&ipv6_addr).map(IpAddress::Ipv6),
+    });
+
+    let mut response_segments = vec![RtnlSegment::NewRoute(new_route_segment(
+        request_segment.header(),
+        table,
+        &route,
+    ))];
+
+    finish_response(request_segment.header(), false, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+pub(super) fn do_new_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = RouteRequest::from_segment(request_segment)?;
+    let new_route = request.new_route()?;
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    route::modify_fib(|fib| {
+        // Like Linux, routes conflict if they have the same destination and the same metric.
+        let conflicts =
+            |route: &FibRoute| route.dst == new_route.dst && route.metric == new_route.metric;
+
+        let has_conflicts = fib.all_routes(request.table).iter().any(conflicts);
+        if has_conflicts && flags.contains(NewRequestFlags::EXCL) {
+            return_errno_with_message!(Errno::EEXIST, "the route already exists");
+        }
+        if !has_conflicts && !flags.contains(NewRequestFlags::CREATE) {
+            return_errno_with_message!(Errno::ENOENT, "the route does not exist");
+        }
+
+        let routes = fib.table_mut(request.table).routes_mut();
+        match routes.iter().position(conflicts) {
+            Some(pos) if flags.contains(NewRequestFlags::REPLACE) => routes[pos] = new_route,
+            // The routes that are implied by the ifaces cannot be replaced, but they are shadowed
+            // by the new route.
+            _ => routes.push(new_route),
+        }
+
+        Ok(Vec::new())
+    })
+}
+
+pub(super) fn do_del_route(request_segment: &RouteSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = RouteRequest::from_segment(request_segment)?;
+
+    route::modify_fib(|fib| {
+        let routes = fib.table_mut(request.table).routes_mut();
+        if let Some(pos) = routes.iter().position(|route| request.matches(route)) {
+            routes.remove(pos);
+            return Ok(Vec::new());
+        }
+
+        // The routes learned by the ifaces can also be deleted.
+        if request.table == TABLE_MAIN && fib.remove_learned_route(|route| request.matches(route)) {
+            return Ok(Vec::new());
+        }
+
+        return_errno_with_message!(Errno::ESRCH, "the route does not exist");
+    })
+}
+
+/// A request to add or delete a route.
+struct RouteRequest {
+    table: u32,
+    dst: IpCidr,
+    /// The type of the route, or `None` if it is not specified.
+    type_: Option<RouteType>,
+    /// The routing protocol, or `None` if it is not specified.
+    protocol: Option<u8>,
+    gateway: Option<IpAddress>,
+    oif: Option<u32>,
+    metric: Option<u32>,
+    pref_src: Option<IpAddress>,
+}
+
+impl RouteRequest {
+    fn from_segment(request_segment: &RouteSegment) -> Result<Self> {
+        let body = request_segment.body();
+
+        let table = request_segment
+            .attrs()
+            .iter()
+            .find_map(|attr| match attr {
+                RouteAttr::Table(table) => Some(*table),
+                _ => None,
+            })
+            .unwrap_or(body.table as u32);
+        let table = if table == RtTable::UNSPEC as u32 {
+            TABLE_MAIN
+        } else {
+            table
+        };
+
+        let type_ = match body.type_ {
+            RtType::UNSPEC => None,
+            RtType::UNICAST => Some(RouteType::Unicast),
+            RtType::BLACKHOLE => Some(RouteType::Blackhole),
+            RtType::UNREACHABLE => Some(RouteType::Unreachable),
+            RtType::PROHIBIT => Some(RouteType::Prohibit),
+            RtType::THROW => Some(RouteType::Throw),
+            _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the route type is not supported"),
+        };
+
+        let protocol = match body.protocol {
+            RtProtocol::UNSPEC => None,
+            protocol => Some(protocol as u8),
+        };
+
+        let unspecified = unspecified_addr_of(body.family)?;
+        let is_same_family = |addr: &IpAddress| addr.version() == unspecified.version();
+
+        let mut dst_addr = None;
+        let mut gateway = None;
+        let mut oif = None;
+        let mut metric = None;
+        let mut pref_src = None;
+        for attr in request_segment.attrs() {
+            match attr {
+                RouteAttr::Dst(addr) => dst_addr = Some(IpAddress::from(*addr)),
+                RouteAttr::Gateway(addr) => gateway = Some(IpAddress::from(*addr)),
+                RouteAttr::Oif(index) => oif = Some(*index),
+                RouteAttr::Priority(priority) => metric = Some(*priority),
+                RouteAttr::PrefSrc(addr) => pref_src = Some(IpAddress::from(*addr)),
+                _ => (),
+            }
+        }
+
+        if gateway.is_some_and(|addr| !is_same_family(&addr))
+            || pref_src.is_some_and(|addr| !is_same_family(&addr))
+        {
+            return_errno_with_message!(Errno::EINVAL, "the gateway or the source is invalid");
+        }
+
+        Ok(Self {
+            table,
+            dst: parse_prefix(&unspecified, dst_addr, body.dst_len)?,
+            type_,
+            protocol,
+            gateway,
+            oif,
+            metric,
+            pref_src,
+        })
+    }
+
+    /// Creates the route to add.
+    fn new_route(&self) -> Result<FibRoute> {
+        let type_ = self.type_.unwrap_or(RouteType::Unicast);
+        let metric = self.metric.unwrap_or(match self.dst {
+            IpCidr::Ipv4(_) => 0,
+            IpCidr::Ipv6(_) => IPV6_DEFAULT_METRIC,
+        });
+        let protocol = self.protocol.unwrap_or(RtProtocol::UNSPEC as u8);
+
+        if type_ != RouteType::Unicast {
+            return Ok(FibRoute {
+                dst: self.dst,
+                type_,
+                gateway: None,
+                oif: None,
+                pref_src: None,
+                metric,
+                protocol,
+            });
+        }
+
+        let mut ifaces =
+            iter_all_ifaces().filter(|iface| self.oif.is_none_or(|oif| oif == iface.index()));
+        let iface = if let Some(gateway) = self.gateway {
+            // Like Linux, the gateway must be reachable from the output interface.
+            ifaces
+                .find(|iface| iface_subnets(iface).any(|subnet| subnet.contains_addr(&gateway)))
+                .ok_or_else(|| {
+                    Error::with_message(Errno::ENETUNREACH, "the gateway is unreachable")
+                })?
+        } else if self.oif.is_some() {
+            ifaces
+                .next()
+                .ok_or_else(|| Error::with_message(Errno::ENODEV, "the interface does not exist"))?
+        } else {
+            return_errno_with_message!(Errno::ENODEV, "the output interface is not specified");
+        };
+
+        if self
+            .pref_src
+            .is_some_and(|pref_src| !iface_has_addr(&iface, &pref_src))
+        {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the preferred source is not an address of the interface"
+            );
+        }
+
+        Ok(FibRoute {
+            dst: self.dst,
+            type_,
+            gateway: self.gateway,
+            oif: Some(iface.index()),
+            pref_src: self.pref_src,
+            metric,
+            protocol,
+        })
+    }
+
+    /// Returns whether the route matches the request to delete routes.
+    ///
+    /// Like Linux, the attributes that are not specified in the request match any routes.
+    fn matches(&self, route: &FibRoute) -> bool {
+        route.dst == self.dst
+            && self.type_.is_none_or(|type_| type_ == route.type_)
+            && self
+                .protocol
+                .is_none_or(|protocol| protocol == route.protocol)
+            && self
+                .gateway
+                .is_none_or(|gateway| Some(gateway) == route.gateway)
+            && self.oif.is_none_or(|oif| Some(oif) == route.oif)
+            && self.metric.is_none_or(|metric| metric == route.metric)
+    }
+}
+
+fn new_route_segment(request_header: &CMsgSegHdr, table: u32, route: &FibRoute) -> RouteSegment {
+    let header = CMsgSegHdr {
+        len: 0,
+        type_: CSegmentType::NEWROUTE as _,
+        flags: SegHdrCommonFlags::empty().bits(),
+        seq: request_header.seq,
+        pid: request_header.pid,
+    };
+
+    let (type_, scope) = match route.type_ {
+        RouteType::Unicast if route.gateway.is_some() => (RtType::UNICAST, RtScope::UNIVERSE),
+        // Like Linux, IPv6 routes always have the global scope.
+        RouteType::Unicast => match route.dst {
+            IpCidr::Ipv4(_) => (RtType::UNICAST, RtScope::LINK),
+            IpCidr::Ipv6(_) => (RtType::UNICAST, RtScope::UNIVERSE),
+        },
+        RouteType::Local => (RtType::LOCAL, RtScope::HOST),
+        RouteType::Blackhole => (RtType::BLACKHOLE, RtScope::UNIVERSE),
+        RouteType::Unreachable => (RtType::UNREACHABLE, RtScope::UNIVERSE),
+        RouteType::Prohibit => (RtType::PROHIBIT, RtScope::UNIVERSE),
+        RouteType::Throw => (RtType::THROW, RtScope::UNIVERSE),
+    };
+
+    let body = RouteSegmentBody {
+        family: family_of(&route.dst) as _,
+        dst_len: route.dst.prefix_len(),
+        src_len: 0,
+        tos: 0,
+        table: u8::try_from(table).unwrap_or(RtTable::COMPAT as u8),
+        protocol: RtProtocol::try_from(route.protocol).unwrap_or(RtProtocol::UNSPEC),
+        scope,
+        type_,
+        flags: 0,
+    };
+
+    let mut attrs = vec![RouteAttr::Table(table)];
+    // Like Linux, the destination is omitted for default routes.
+    if route.dst.prefix_len() != 0 {
+        attrs.push(RouteAttr::Dst(IpAddrBytes::from(route.dst.address())));
+    }
+    if route.metric != 0 {
+        attrs.push(RouteAttr::Priority(route.metric));
+    }
+    if let Some(pref_src) = route.pref_src {
+        attrs.push(RouteAttr::PrefSrc(IpAddrBytes::from(pref_src)));
+    }
+    if let Some(gateway) = route.gateway {
+        attrs.push(RouteAttr::Gateway(IpAddrBytes::from(gateway)));
+    }
+    if let Some(oif) = route.oif {
+        attrs.push(RouteAttr::Oif(oif));
+    }
+
+    RouteSegment::new(header, body, attrs)
+}
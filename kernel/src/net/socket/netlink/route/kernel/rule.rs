@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handle rule-related requests.
+
+use aster_bigtcp::wire::{IpAddress, IpCidr};
+
+use super::util::{
+    check_net_admin, finish_response, parse_prefix, unspecified_addr_of, FamilyFilter,
+};
+use crate::{
+    net::{
+        route::{self, FibRule, RuleAction},
+        socket::netlink::{
+            message::{
+                CMsgSegHdr, CSegmentType, GetRequestFlags, NewRequestFlags, SegHdrCommonFlags,
+            },
+            route::message::{
+                FibRuleFlags, FrAction, IpAddrBytes, RtTable, RtnlSegment, RuleAttr, RuleSegment,
+                RuleSegmentBody,
+            },
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+pub(super) fn do_get_rule(request_segment: &RuleSegment) -> Result<Vec<RtnlSegment>> {
+    let dump_all = {
+        let flags = GetRequestFlags::from_bits_truncate(request_segment.header().flags);
+        flags.contains(GetRequestFlags::DUMP)
+    };
+    if !dump_all {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "GETRULE only supports dump requests");
+    }
+
+    let family_filter = FamilyFilter::from_family(request_segment.body().family);
+
+    let mut response_segments: Vec<RtnlSegment> = route::with_fib(|fib| {
+        fib.rules()
+            .iter()
+            .filter(|rule| {
+                if rule.is_ipv6 {
+                    family_filter.contains_ipv6()
+                } else {
+                    family_filter.contains_ipv4()
+                }
+            })
+            .map(|rule| new_rule_segment(request_segment.header(), rule))
+            .map(RtnlSegment::NewRule)
+            .collect()
+    });
+
+    finish_response(request_segment.header(), dump_all, &mut response_segments);
+
+    Ok(response_segments)
+}
+
+pub(super) fn do_new_rule(request_segment: &RuleSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = RuleRequest::from_segment(request_segment)?;
+    let Some(action) = request.action else {
+        return_errno_with_message!(Errno::EINVAL, "the rule action is not specified");
+    };
+
+    let flags = NewRequestFlags::from_bits_truncate(request_segment.header().flags);
+    route::modify_fib(|fib| {
+        let new_rule = FibRule {
+            is_ipv6: request.is_ipv6,
+            priority: request
+                .priority
+                .unwrap_or_else(|| fib.default_rule_priority(request.is_ipv6)),
+            src: request.src,
+            dst: request.dst,
+            invert: request.invert,
+            action,
+            protocol: request.protocol.unwrap_or(0),
+        };
+
+        // Like Linux, identical rules are allowed unless `EXCL` is specified.
+        if flags.contains(NewRequestFlags::EXCL) && fib.rules().contains(&new_rule) {
+            return_errno_with_message!(Errno::EEXIST, "the rule already exists");
+        }
+
+        fib.add_rule(new_rule);
+
+        Ok(Vec::new())
+    })
+}
+
+pub(super) fn do_del_rule(request_segment: &RuleSegment) -> Result<Vec<RtnlSegment>> {
+    check_net_admin()?;
+
+    let request = RuleRequest::from_segment(request_segment)?;
+
+    route::modify_fib(|fib| {
+        if !fib.remove_rule(|rule| request.matches(rule)) {
+            return_errno_with_message!(Errno::ENOENT, "the rule does not exist");
+        }
+
+        Ok(Vec::new())
+    })
+}
+
+/// A request to add or delete a rule.
+struct RuleRequest {
+    is_ipv6: bool,
+    /// The priority, or `None` if it is not specified.
+    priority: Option<u32>,
+    src: Option<IpCidr>,
+    dst: Option<IpCidr>,
+    invert: bool,
+    /// The action, or `None` if it is not specified.
+    action: Option<RuleAction>,
+    /// The routing protocol, or `None` if it is not specified.
+    protocol: Option<u8>,
+}
+
+impl RuleRequest {
+    fn from_segment(request_segment: &RuleSegment) -> Result<Self> {
+        let body = request_segment.body();
+
+        let unspecified = unspecified_addr_of(body.family)?;
+        let is_ipv6 = matches!(unspecified, IpAddress::Ipv6(_));
+
+        let mut table = body.table as u32;
+        let mut priority = None;
+        let mut src_addr = None;
+        let mut dst_addr = None;
+        let mut protocol = None;
+        for attr in request_segment.attrs() {
+            match attr {
+                RuleAttr::Table(id) => table = *id,
+                RuleAttr::Priority(value) => priority = Some(*value),
+                RuleAttr::Src(addr) => src_addr = Some(IpAddress::from(*addr)),
+                RuleAttr::Dst(addr) => dst_addr = Some(IpAddress::from(*addr)),
+                RuleAttr::Protocol(value) => protocol = Some(*value),
+                RuleAttr::IifName(_) | RuleAttr::OifName(_) | RuleAttr::FwMark(_) => {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "selecting packets by interfaces or marks is not supported"
+                    );
+                }
+            }
+        }
+
+        // Like Linux, a prefix that matches all addresses is the same as no prefix.
+        let src = Some(parse_prefix(&unspecified, src_addr, body.src_len)?)
+            .filter(|cidr| cidr.prefix_len() != 0);
+        let dst = Some(parse_prefix(&unspecified, dst_addr, body.dst_len)?)
+            .filter(|cidr| cidr.prefix_len() != 0);
+
+        let action = match body.action {
+            FrAction::UNSPEC => None,
+            FrAction::TO_TBL if table == RtTable::UNSPEC as u32 => {
+                return_errno_with_message!(Errno::EINVAL, "the routing table is not specified");
+            }
+            FrAction::TO_TBL => Some(RuleAction::ToTable(table)),
+            FrAction::GOTO => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "GOTO rules are not supported");
+            }
+            FrAction::NOP => Some(RuleAction::Nop),
+            FrAction::BLACKHOLE => Some(RuleAction::Blackhole),
+            FrAction::UNREACHABLE => Some(RuleAction::Unreachable),
+            FrAction::PROHIBIT => Some(RuleAction::Prohibit),
+        };
+
+        Ok(Self {
+            is_ipv6,
+            priority,
+            src,
+            dst,
+            invert: body.flags.contains(FibRuleFlags::INVERT),
+            action,
+            protocol,
+        })
+    }
+
+    /// Returns whether the rule matches the request to delete rules.
+    ///
+    /// Like Linux, the selectors and the attributes that are not specified in the request match
+    /// any rules.
+    fn matches(&self, rule: &FibRule) -> bool {
+        rule.is_ipv6 == self.is_ipv6
+            && self
+                .priority
+                .is_none_or(|priority| priority == rule.priority)
+            && self.src.is_none_or(|src| Some(src) == rule.src)
+            && self.dst.is_none_or(|dst| Some(dst) == rule.dst)
+            && self.action.is_none_or(|action| action == rule.action)
+            && self
+                .protocol
+                .is_none_or(|protocol| protocol == rule.protocol)
+    }
+}
+
+fn new_rule_segment(request_header: &CMsgSegHdr, rule: &FibRule) -> RuleSegment {
+    let header = CMsgSegHdr {
+        len: 0,
+        type_: CSegmentType::NEWRULE as _,
+        flags: SegHdrCommonFlags::empty().bits(),
+        seq: request_header.seq,
+        pid: request_header.pid,
+    };
+
+    let (action, table) = match rule.action {
+        RuleAction::ToTable(table) => (FrAction::TO_TBL, table),
+        RuleAction::Nop => (FrAction::NOP, RtTable::UNSPEC as u32),
+        RuleAction::Blackhole => (FrAction::BLACKHOLE, RtTable::UNSPEC as u32),
+        RuleAction::Unreachable => (FrAction::UNREACHABLE, RtTable::UNSPEC as u32),
+        RuleAction::Prohibit => (FrAction::PROHIBIT, RtTable::UNSPEC as u32),
+    };
+
+    let mut flags = FibRuleFlags::empty();
+    if rule.invert {
+        flags |= FibRuleFlags::INVERT;
+    }
+
+    let family = if rule.is_ipv6 {
+        CSocketAddrFamily::AF_INET6
+    } else {
+        CSocketAddrFamily::AF_INET
+    };
+
+    let body = RuleSegmentBody {
+        family: family as _,
+        dst_len: rule.dst.map_or(0, |dst| dst.prefix_len()),
+        src_len: rule.src.map_or(0, |src| src.prefix_len()),
+        tos: 0,
+        table: u8::try_from(table).unwrap_or(RtTable::COMPAT as u8),
+        action,
+        flags,
+    };
+
+    let mut attrs = vec![RuleAttr::Table(table)];
+    // Like Linux, the priority is omitted if it is zero.
+    if rule.priority != 0 {
+        attrs.push(RuleAttr::Priority(rule.priority));
+    }
+    if let Some(src) = rule.src {
+        attrs.push(RuleAttr::Src(IpAddrBytes::from(src.address())));
+    }
+    if let Some(dst) = rule.dst {
+        attrs.push(RuleAttr::Dst(IpAddrBytes::from(dst.address())));
+    }
+    attrs.push(RuleAttr::Protocol(rule.protocol));
+
+    RuleSegment::new(header, body, attrs)
+}
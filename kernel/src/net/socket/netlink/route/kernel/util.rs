@@ -1,11 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_bigtcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
 use crate::{
-    net::socket::netlink::{
-        message::{CMsgSegHdr, DoneSegment, ProtocolSegment, SegHdrCommonFlags},
-        route::message::RtnlSegment,
+    net::{
+        route::max_prefix_len,
+        socket::netlink::{
+            message::{CMsgSegHdr, DoneSegment, ProtocolSegment, SegHdrCommonFlags},
+            route::message::RtnlSegment,
+        },
     },
     prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    util::net::CSocketAddrFamily,
 };
 
 /// Finishes a response message.
@@ -37,3 +44,90 @@ fn add_multi_flag(response_segments: &mut Vec<RtnlSegment>) {
         header.flags = flags.bits();
     }
 }
+
+/// Checks whether the current thread is allowed to modify the network configurations.
+pub fn check_net_admin() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+    if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "modifying network configurations requires `CAP_NET_ADMIN`"
+        );
+    }
+
+    Ok(())
+}
+
+/// The address family that a request is interested in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FamilyFilter {
+    Ipv4,
+    Ipv6,
+    All,
+}
+
+impl FamilyFilter {
+    /// Creates the filter from the family in a dump request.
+    ///
+    /// Like Linux, families other than `AF_INET` and `AF_INET6` do not filter anything.
+    pub fn from_family(family: i32) -> Self {
+        match CSocketAddrFamily::try_from(family) {
+            Ok(CSocketAddrFamily::AF_INET) => Self::Ipv4,
+            Ok(CSocketAddrFamily::AF_INET6) => Self::Ipv6,
+            _ => Self::All,
+        }
+    }
+
+    pub fn contains_ipv4(self) -> bool {
+        self != Self::Ipv6
+    }
+
+    pub fn contains_ipv6(self) -> bool {
+        self != Self::Ipv4
+    }
+}
+
+/// Returns the address family of the prefix.
+pub fn family_of(ip_cidr: &IpCidr) -> CSocketAddrFamily {
+    match ip_cidr {
+        IpCidr::Ipv4(_) => CSocketAddrFamily::AF_INET,
+        IpCidr::Ipv6(_) => CSocketAddrFamily::AF_INET6,
+    }
+}
+
+/// Returns whether the prefix passes the filter.
+pub fn is_included(family_filter: FamilyFilter, ip_cidr: &IpCidr) -> bool {
+    match ip_cidr {
+        IpCidr::Ipv4(_) => family_filter.contains_ipv4(),
+        IpCidr::Ipv6(_) => family_filter.contains_ipv6(),
+    }
+}
+
+/// Returns the unspecified address of the family in a request.
+pub fn unspecified_addr_of(family: i32) -> Result<IpAddress> {
+    match CSocketAddrFamily::try_from(family) {
+        Ok(CSocketAddrFamily::AF_INET) => Ok(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED)),
+        Ok(CSocketAddrFamily::AF_INET6) => Ok(IpAddress::Ipv6(Ipv6Address::UNSPECIFIED)),
+        _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "the family is not supported"),
+    }
+}
+
+/// Parses a prefix in a request.
+///
+/// If the address is omitted, the prefix length must be zero and the prefix matches all
+/// addresses of the family.
+pub fn parse_prefix(
+    unspecified: &IpAddress,
+    addr: Option<IpAddress>,
+    prefix_len: u8,
+) -> Result<IpCidr> {
+    if prefix_len > max_prefix_len(unspecified)
+        || (addr.is_none() && prefix_len != 0)
+        || addr.is_some_and(|addr| addr.version() != unspecified.version())
+    {
+        return_errno_with_message!(Errno::EINVAL, "the prefix is invalid");
+    }
+
+    Ok(IpCidr::new(addr.unwrap_or(*unspecified), prefix_len))
+}
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::IFNAME_SIZE;
+use super::{IpAddrBytes, IFNAME_SIZE};
 use crate::{
     net::socket::netlink::message::{Attribute, CAttrHeader},
     prelude::*,
@@ -29,8 +29,8 @@ enum AddrAttrClass {
 
 #[derive(Debug)]
 pub enum AddrAttr {
-    Address([u8; 4]),
-    Local([u8; 4]),
+    Address(IpAddrBytes),
+    Local(IpAddrBytes),
     Label(CString),
 }
 
@@ -51,30 +51,27 @@ impl Attribute for AddrAttr {
 
     fn payload_as_bytes(&self) -> &[u8] {
         match self {
-            AddrAttr::Address(address) => address,
-            AddrAttr::Local(local) => local,
+            AddrAttr::Address(address) => address.as_bytes(),
+            AddrAttr::Local(local) => local.as_bytes(),
             AddrAttr::Label(label) => label.as_bytes_with_nul(),
         }
     }
 
-    fn read_from(reader: &mut dyn MultiRead) -> Result<Self>
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
     where
         Self: Sized,
     {
-        let header = reader.read_val::<CAttrHeader>()?;
         // TODO: Currently, `IS_NET_BYTEORDER_MASK` and `IS_NESTED_MASK` are ignored.
-        let res = match AddrAttrClass::try_from(header.type_())? {
-            AddrAttrClass::ADDRESS => Self::Address(reader.read_val()?),
-            AddrAttrClass::LOCAL => Self::Local(reader.read_val()?),
-            AddrAttrClass::LABEL => Self::Label(reader.read_cstring_with_max_len(IFNAME_SIZE)?),
+        let res = match AddrAttrClass::try_from(header.type_()) {
+            Ok(AddrAttrClass::ADDRESS) => Self::Address(IpAddrBytes::read_from(header, reader)?),
+            Ok(AddrAttrClass::LOCAL) => Self::Local(IpAddrBytes::read_from(header, reader)?),
+            Ok(AddrAttrClass::LABEL) => Self::Label(reader.read_cstring_with_max_len(IFNAME_SIZE)?),
             class => {
-                // FIXME: Netlink should ignore all unknown attributes.
-                // See the reference in `LinkAttr::read_from`.
-                warn!("address attribute `{:?}` is not supported", class);
-                return_errno_with_message!(Errno::EINVAL, "unsupported address attribute");
+                debug!("address attribute `{:?}` is ignored", class);
+                return Ok(None);
             }
         };
 
-        Ok(res)
+        Ok(Some(res))
     }
 }
@@ -2,7 +2,7 @@
 
 use super::IFNAME_SIZE;
 use crate::{
-    net::socket::netlink::message::{Attribute, CAttrHeader},
+    net::socket::netlink::message::{Attribute, CAttrHeader, Nested},
     prelude::*,
     util::MultiRead,
 };
@@ -85,8 +85,10 @@ enum LinkAttrClass {
 pub enum LinkAttr {
     Name(CString),
     Mtu(u32),
+    Master(u32),
     TxqLen(u32),
     LinkMode(u8),
+    LinkInfo(Nested),
     ExtMask(RtExtFilter),
 }
 
@@ -95,8 +97,10 @@ impl LinkAttr {
         match self {
             LinkAttr::Name(_) => LinkAttrClass::IFNAME,
             LinkAttr::Mtu(_) => LinkAttrClass::MTU,
+            LinkAttr::Master(_) => LinkAttrClass::MASTER,
             LinkAttr::TxqLen(_) => LinkAttrClass::TXQLEN,
             LinkAttr::LinkMode(_) => LinkAttrClass::LINKMODE,
+            LinkAttr::LinkInfo(_) => LinkAttrClass::LINKINFO,
             LinkAttr::ExtMask(_) => LinkAttrClass::EXT_MASK,
         }
     }
@@ -111,34 +115,34 @@ impl Attribute for LinkAttr {
         match self {
             LinkAttr::Name(name) => name.as_bytes_with_nul(),
             LinkAttr::Mtu(mtu) => mtu.as_bytes(),
+            LinkAttr::Master(master) => master.as_bytes(),
             LinkAttr::TxqLen(txq_len) => txq_len.as_bytes(),
             LinkAttr::LinkMode(link_mode) => link_mode.as_bytes(),
+            LinkAttr::LinkInfo(link_info) => link_info.as_bytes(),
             LinkAttr::ExtMask(ext_filter) => ext_filter.as_bytes(),
         }
     }
 
-    fn read_from(reader: &mut dyn MultiRead) -> Result<Self>
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
     where
         Self: Sized,
     {
-        let header = reader.read_val::<CAttrHeader>()?;
         // TODO: Currently, `IS_NET_BYTEORDER_MASK` and `IS_NESTED_MASK` are ignored.
-        let res = match LinkAttrClass::try_from(header.type_())? {
-            LinkAttrClass::IFNAME => Self::Name(reader.read_cstring_with_max_len(IFNAME_SIZE)?),
-            LinkAttrClass::MTU => Self::Mtu(reader.read_val()?),
-            LinkAttrClass::TXQLEN => Self::TxqLen(reader.read_val()?),
-            LinkAttrClass::LINKMODE => Self::LinkMode(reader.read_val()?),
-            LinkAttrClass::EXT_MASK => Self::ExtMask(reader.read_val()?),
+        let res = match LinkAttrClass::try_from(header.type_()) {
+            Ok(LinkAttrClass::IFNAME) => Self::Name(reader.read_cstring_with_max_len(IFNAME_SIZE)?),
+            Ok(LinkAttrClass::MTU) => Self::Mtu(reader.read_val()?),
+            Ok(LinkAttrClass::MASTER) => Self::Master(reader.read_val()?),
+            Ok(LinkAttrClass::TXQLEN) => Self::TxqLen(reader.read_val()?),
+            Ok(LinkAttrClass::LINKMODE) => Self::LinkMode(reader.read_val()?),
+            Ok(LinkAttrClass::LINKINFO) => Self::LinkInfo(Nested::read_from(header, reader)?),
+            Ok(LinkAttrClass::EXT_MASK) => Self::ExtMask(reader.read_val()?),
             class => {
-                // FIXME: Netlink should ignore all unknown attributes.
-                // But how to decide the payload type if the class is unknown?
-                // Reference: https://docs.kernel.org/userspace-api/netlink/intro.html#unknown-attributes
-                warn!("link attribute `{:?}` is not supported", class);
-                return_errno_with_message!(Errno::EINVAL, "unsupported link attribute");
+                debug!("link attribute `{:?}` is ignored", class);
+                return Ok(None);
             }
         };
 
-        Ok(res)
+        Ok(Some(res))
     }
 }
 
@@ -159,3 +163,222 @@ bitflags! {
         const MST = 1 << 7;
     }
 }
+
+/// Attributes nested in [`LinkAttr::LinkInfo`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_link.h#L769>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[allow(non_camel_case_types)]
+enum LinkInfoAttrClass {
+    UNSPEC = 0,
+    KIND = 1,
+    DATA = 2,
+    XSTATS = 3,
+    SLAVE_KIND = 4,
+    SLAVE_DATA = 5,
+}
+
+#[derive(Debug)]
+pub enum LinkInfoAttr {
+    Kind(CString),
+    Data(Nested),
+    SlaveKind(CString),
+    SlaveData(Nested),
+}
+
+impl LinkInfoAttr {
+    fn class(&self) -> LinkInfoAttrClass {
+        match self {
+            LinkInfoAttr::Kind(_) => LinkInfoAttrClass::KIND,
+            LinkInfoAttr::Data(_) => LinkInfoAttrClass::DATA,
+            LinkInfoAttr::SlaveKind(_) => LinkInfoAttrClass::SLAVE_KIND,
+            LinkInfoAttr::SlaveData(_) => LinkInfoAttrClass::SLAVE_DATA,
+        }
+    }
+}
+
+impl Attribute for LinkInfoAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            LinkInfoAttr::Kind(kind) => kind.as_bytes_with_nul(),
+            LinkInfoAttr::Data(data) => data.as_bytes(),
+            LinkInfoAttr::SlaveKind(kind) => kind.as_bytes_with_nul(),
+            LinkInfoAttr::SlaveData(data) => data.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match LinkInfoAttrClass::try_from(header.type_()) {
+            Ok(LinkInfoAttrClass::KIND) => Self::Kind(read_kind(header, reader)?),
+            Ok(LinkInfoAttrClass::DATA) => Self::Data(Nested::read_from(header, reader)?),
+            Ok(LinkInfoAttrClass::SLAVE_KIND) => Self::SlaveKind(read_kind(header, reader)?),
+            Ok(LinkInfoAttrClass::SLAVE_DATA) => {
+                Self::SlaveData(Nested::read_from(header, reader)?)
+            }
+            class => {
+                debug!("link info attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// The size limit for link kinds (e.g., "bridge").
+const LINK_KIND_SIZE: usize = 16;
+
+/// Reads a link kind.
+///
+/// Unlike link names, link kinds are not always terminated by a nul byte (e.g., `iproute2`
+/// sends them without one), so the kind ends at the first nul byte or at the end of the payload.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/lib/nlattr.c#L782>.
+fn read_kind(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<CString> {
+    let payload_len = header.payload_len();
+    if payload_len > LINK_KIND_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "the link kind is too long");
+    }
+
+    let mut bytes = vec![0u8; payload_len];
+    reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+    if let Some(nul_pos) = bytes.iter().position(|byte| *byte == 0) {
+        bytes.truncate(nul_pos);
+    }
+
+    Ok(CString::new(bytes).unwrap())
+}
+
+/// Bridge attributes nested in [`LinkInfoAttr::Data`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_link.h#L481>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[allow(non_camel_case_types)]
+enum BridgeAttrClass {
+    UNSPEC = 0,
+    FORWARD_DELAY = 1,
+    HELLO_TIME = 2,
+    MAX_AGE = 3,
+    AGEING_TIME = 4,
+    STP_STATE = 5,
+    PRIORITY = 6,
+    // TODO: This enum is not exhaustive.
+}
+
+#[derive(Debug)]
+pub enum BridgeAttr {
+    /// The ageing time in centiseconds (i.e., the `clock_t` unit).
+    AgeingTime(u32),
+    StpState(u32),
+}
+
+impl BridgeAttr {
+    fn class(&self) -> BridgeAttrClass {
+        match self {
+            BridgeAttr::AgeingTime(_) => BridgeAttrClass::AGEING_TIME,
+            BridgeAttr::StpState(_) => BridgeAttrClass::STP_STATE,
+        }
+    }
+}
+
+impl Attribute for BridgeAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            BridgeAttr::AgeingTime(ageing_time) => ageing_time.as_bytes(),
+            BridgeAttr::StpState(stp_state) => stp_state.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match BridgeAttrClass::try_from(header.type_()) {
+            Ok(BridgeAttrClass::AGEING_TIME) => Self::AgeingTime(reader.read_val()?),
+            Ok(BridgeAttrClass::STP_STATE) => Self::StpState(reader.read_val()?),
+            class => {
+                debug!("bridge attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
+
+/// Bridge port attributes nested in [`LinkInfoAttr::SlaveData`].
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_link.h#L564>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[allow(non_camel_case_types)]
+enum BridgePortAttrClass {
+    UNSPEC = 0,
+    STATE = 1,
+    PRIORITY = 2,
+    COST = 3,
+    MODE = 4,
+    GUARD = 5,
+    PROTECT = 6,
+    FAST_LEAVE = 7,
+    LEARNING = 8,
+    UNICAST_FLOOD = 9,
+    // TODO: This enum is not exhaustive.
+}
+
+#[derive(Debug)]
+pub enum BridgePortAttr {
+    State(u8),
+    Guard(u8),
+}
+
+impl BridgePortAttr {
+    fn class(&self) -> BridgePortAttrClass {
+        match self {
+            BridgePortAttr::State(_) => BridgePortAttrClass::STATE,
+            BridgePortAttr::Guard(_) => BridgePortAttrClass::GUARD,
+        }
+    }
+}
+
+impl Attribute for BridgePortAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            BridgePortAttr::State(state) => state.as_bytes(),
+            BridgePortAttr::Guard(guard) => guard.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        let res = match BridgePortAttrClass::try_from(header.type_()) {
+            Ok(BridgePortAttrClass::STATE) => Self::State(reader.read_val()?),
+            Ok(BridgePortAttrClass::GUARD) => Self::Guard(reader.read_val()?),
+            class => {
+                debug!("bridge port attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
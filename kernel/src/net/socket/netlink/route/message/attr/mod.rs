@@ -1,7 +1,57 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_bigtcp::wire::{IpAddress, Ipv4Address, Ipv6Address};
+
+use crate::{net::socket::netlink::message::CAttrHeader, prelude::*, util::MultiRead};
+
 pub mod addr;
 pub mod link;
+pub mod route;
+pub mod rule;
 
 /// The size limit for interface names.
 const IFNAME_SIZE: usize = 16;
+
+/// An IPv4 or IPv6 address in an attribute payload.
+///
+/// The address family is determined by the payload length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpAddrBytes {
+    V4([u8; 4]),
+    V6([u8; 16]),
+}
+
+impl IpAddrBytes {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::V4(bytes) => bytes,
+            Self::V6(bytes) => bytes,
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Self> {
+        match header.payload_len() {
+            4 => Ok(Self::V4(reader.read_val()?)),
+            16 => Ok(Self::V6(reader.read_val()?)),
+            _ => return_errno_with_message!(Errno::EINVAL, "the address length is invalid"),
+        }
+    }
+}
+
+impl From<IpAddress> for IpAddrBytes {
+    fn from(value: IpAddress) -> Self {
+        match value {
+            IpAddress::Ipv4(ipv4_addr) => Self::V4(ipv4_addr.octets()),
+            IpAddress::Ipv6(ipv6_addr) => Self::V6(ipv6_addr.octets()),
+        }
+    }
+}
+
+impl From<IpAddrBytes> for IpAddress {
+    fn from(value: IpAddrBytes) -> Self {
+        match value {
+            IpAddrBytes::V4(bytes) => IpAddress::Ipv4(Ipv4Address::from(bytes)),
+            IpAddrBytes::V6(bytes) => IpAddress::Ipv6(Ipv6Address::from(bytes)),
+        }
+    }
+}
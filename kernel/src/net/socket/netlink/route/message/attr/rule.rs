@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{IpAddrBytes, IFNAME_SIZE};
+use crate::{
+    net::socket::netlink::message::{Attribute, CAttrHeader},
+    prelude::*,
+    util::MultiRead,
+};
+
+/// Rule-related attributes.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/fib_rules.h#L46>.
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u16)]
+#[expect(non_camel_case_types)]
+enum RuleAttrClass {
+    UNSPEC = 0,
+    DST = 1,
+    SRC = 2,
+    IIFNAME = 3,
+    GOTO = 4,
+    UNUSED2 = 5,
+    PRIORITY = 6,
+    UNUSED3 = 7,
+    UNUSED4 = 8,
+    UNUSED5 = 9,
+    FWMARK = 10,
+    FLOW = 11,
+    TUN_ID = 12,
+    SUPPRESS_IFGROUP = 13,
+    SUPPRESS_PREFIXLEN = 14,
+    TABLE = 15,
+    FWMASK = 16,
+    OIFNAME = 17,
+    PAD = 18,
+    L3MDEV = 19,
+    UID_RANGE = 20,
+    PROTOCOL = 21,
+    IP_PROTO = 22,
+    SPORT_RANGE = 23,
+    DPORT_RANGE = 24,
+}
+
+#[derive(Debug)]
+pub enum RuleAttr {
+    Dst(IpAddrBytes),
+    Src(IpAddrBytes),
+    IifName(CString),
+    Priority(u32),
+    FwMark(u32),
+    Table(u32),
+    OifName(CString),
+    Protocol(u8),
+}
+
+impl RuleAttr {
+    fn class(&self) -> RuleAttrClass {
+        match self {
+            RuleAttr::Dst(_) => RuleAttrClass::DST,
+            RuleAttr::Src(_) => RuleAttrClass::SRC,
+            RuleAttr::IifName(_) => RuleAttrClass::IIFNAME,
+            RuleAttr::Priority(_) => RuleAttrClass::PRIORITY,
+            RuleAttr::FwMark(_) => RuleAttrClass::FWMARK,
+            RuleAttr::Table(_) => RuleAttrClass::TABLE,
+            RuleAttr::OifName(_) => RuleAttrClass::OIFNAME,
+            RuleAttr::Protocol(_) => RuleAttrClass::PROTOCOL,
+        }
+    }
+}
+
+impl Attribute for RuleAttr {
+    fn type_(&self) -> u16 {
+        self.class() as u16
+    }
+
+    fn payload_as_bytes(&self) -> &[u8] {
+        match self {
+            RuleAttr::Dst(dst) => dst.as_bytes(),
+            RuleAttr::Src(src) => src.as_bytes(),
+            RuleAttr::IifName(name) => name.as_bytes_with_nul(),
+            RuleAttr::Priority(priority) => priority.as_bytes(),
+            RuleAttr::FwMark(mark) => mark.as_bytes(),
+            RuleAttr::Table(table) => table.as_bytes(),
+            RuleAttr::OifName(name) => name.as_bytes_with_nul(),
+            RuleAttr::Protocol(protocol) => protocol.as_bytes(),
+        }
+    }
+
+    fn read_from(header: &CAttrHeader, reader: &mut dyn MultiRead) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        // TODO: Currently, `IS_NET_BYTEORDER_MASK` and `IS_NESTED_MASK` are ignored.
+        let res = match RuleAttrClass::try_from(header.type_()) {
+            Ok(RuleAttrClass::DST) => Self::Dst(IpAddrBytes::read_from(header, reader)?),
+            Ok(RuleAttrClass::SRC) => Self::Src(IpAddrBytes::read_from(header, reader)?),
+            Ok(RuleAttrClass::IIFNAME) => {
+                Self::IifName(reader.read_cstring_with_max_len(IFNAME_SIZE)?)
+            }
+            Ok(RuleAttrClass::PRIORITY) => Self::Priority(reader.read_val()?),
+            Ok(RuleAttrClass::FWMARK) => Self::FwMark(reader.read_val()?),
+            Ok(RuleAttrClass::TABLE) => Self::Table(reader.read_val()?),
+            Ok(RuleAttrClass::OIFNAME) => {
+                Self::OifName(reader.read_cstring_with_max_len(IFNAME_SIZE)?)
+            }
+            Ok(RuleAttrClass::PROTOCOL) => Self::Protocol(reader.read_val()?),
+            class => {
+                debug!("rule attribute `{:?}` is ignored", class);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(res))
+    }
+}
@@ -8,10 +8,18 @@
 mod attr;
 mod segment;
 
-pub(super) use attr::{addr::AddrAttr, link::LinkAttr};
+pub(super) use attr::{
+    addr::AddrAttr,
+    link::{BridgeAttr, BridgePortAttr, LinkAttr, LinkInfoAttr},
+    route::RouteAttr,
+    rule::RuleAttr,
+    IpAddrBytes,
+};
 pub(super) use segment::{
     addr::{AddrMessageFlags, AddrSegment, AddrSegmentBody, RtScope},
     link::{LinkSegment, LinkSegmentBody},
+    route::{RouteSegment, RouteSegmentBody, RtProtocol, RtTable, RtType},
+    rule::{FibRuleFlags, FrAction, RuleSegment, RuleSegmentBody},
     RtnlSegment,
 };
 
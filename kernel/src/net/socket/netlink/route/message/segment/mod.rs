@@ -33,9 +33,12 @@ pub mod addr;
 mod legacy;
 pub mod link;
 pub mod route;
+pub mod rule;
 
 use addr::AddrSegment;
 use link::LinkSegment;
+use route::RouteSegment;
+use rule::RuleSegment;
 
 use crate::{
     net::socket::netlink::message::{
@@ -49,9 +52,18 @@ use crate::{
 #[derive(Debug)]
 pub enum RtnlSegment {
     NewLink(LinkSegment),
+    DelLink(LinkSegment),
     GetLink(LinkSegment),
+    SetLink(LinkSegment),
     NewAddr(AddrSegment),
+    DelAddr(AddrSegment),
     GetAddr(AddrSegment),
+    NewRoute(RouteSegment),
+    DelRoute(RouteSegment),
+    GetRoute(RouteSegment),
+    NewRule(RuleSegment),
+    DelRule(RuleSegment),
+    GetRule(RuleSegment),
     Done(DoneSegment),
     Error(ErrorSegment),
 }
@@ -59,12 +71,19 @@ pub enum RtnlSegment {
 impl ProtocolSegment for RtnlSegment {
     fn header(&self) -> &CMsgSegHdr {
         match self {
-            RtnlSegment::NewLink(link_segment) | RtnlSegment::GetLink(link_segment) => {
-                link_segment.header()
-            }
-            RtnlSegment::NewAddr(addr_segment) | RtnlSegment::GetAddr(addr_segment) => {
-                addr_segment.header()
-            }
+            RtnlSegment::NewLink(link_segment)
+            | RtnlSegment::DelLink(link_segment)
+            | RtnlSegment::GetLink(link_segment)
+            | RtnlSegment::SetLink(link_segment) => link_segment.header(),
+            RtnlSegment::NewAddr(addr_segment)
+            | RtnlSegment::DelAddr(addr_segment)
+            | RtnlSegment::GetAddr(addr_segment) => addr_segment.header(),
+            RtnlSegment::NewRoute(route_segment)
+            | RtnlSegment::DelRoute(route_segment)
+            | RtnlSegment::GetRoute(route_segment) => route_segment.header(),
+            RtnlSegment::NewRule(rule_segment)
+            | RtnlSegment::DelRule(rule_segment)
+            | RtnlSegment::GetRule(rule_segment) => rule_segment.header(),
             RtnlSegment::Done(done_segment) => done_segment.header(),
             RtnlSegment::Error(error_segment) => error_segment.header(),
         }
@@ -72,12 +91,19 @@ impl ProtocolSegment for RtnlSegment {
 
     fn header_mut(&mut self) -> &mut CMsgSegHdr {
         match self {
-            RtnlSegment::NewLink(link_segment) | RtnlSegment::GetLink(link_segment) => {
-                link_segment.header_mut()
-            }
-            RtnlSegment::NewAddr(addr_segment) | RtnlSegment::GetAddr(addr_segment) => {
-                addr_segment.header_mut()
-            }
+            RtnlSegment::NewLink(link_segment)
+            | RtnlSegment::DelLink(link_segment)
+            | RtnlSegment::GetLink(link_segment)
+            | RtnlSegment::SetLink(link_segment) => link_segment.header_mut(),
+            RtnlSegment::NewAddr(addr_segment)
+            | RtnlSegment::DelAddr(addr_segment)
+            | RtnlSegment::GetAddr(addr_segment) => addr_segment.header_mut(),
+            RtnlSegment::NewRoute(route_segment)
+            | RtnlSegment::DelRoute(route_segment)
+            | RtnlSegment::GetRoute(route_segment) => route_segment.header_mut(),
+            RtnlSegment::NewRule(rule_segment)
+            | RtnlSegment::DelRule(rule_segment)
+            | RtnlSegment::GetRule(rule_segment) => rule_segment.header_mut(),
             RtnlSegment::Done(done_segment) => done_segment.header_mut(),
             RtnlSegment::Error(error_segment) => error_segment.header_mut(),
         }
@@ -87,8 +113,25 @@ impl ProtocolSegment for RtnlSegment {
         let header = reader.read_val::<CMsgSegHdr>()?;
 
         let segment = match CSegmentType::try_from(header.type_)? {
+            CSegmentType::NEWLINK => RtnlSegment::NewLink(LinkSegment::read_from(header, reader)?),
+            CSegmentType::DELLINK => RtnlSegment::DelLink(LinkSegment::read_from(header, reader)?),
             CSegmentType::GETLINK => RtnlSegment::GetLink(LinkSegment::read_from(header, reader)?),
+            CSegmentType::SETLINK => RtnlSegment::SetLink(LinkSegment::read_from(header, reader)?),
+            CSegmentType::NEWADDR => RtnlSegment::NewAddr(AddrSegment::read_from(header, reader)?),
+            CSegmentType::DELADDR => RtnlSegment::DelAddr(AddrSegment::read_from(header, reader)?),
             CSegmentType::GETADDR => RtnlSegment::GetAddr(AddrSegment::read_from(header, reader)?),
+            CSegmentType::NEWROUTE => {
+                RtnlSegment::NewRoute(RouteSegment::read_from(header, reader)?)
+            }
+            CSegmentType::DELROUTE => {
+                RtnlSegment::DelRoute(RouteSegment::read_from(header, reader)?)
+            }
+            CSegmentType::GETROUTE => {
+                RtnlSegment::GetRoute(RouteSegment::read_from(header, reader)?)
+            }
+            CSegmentType::NEWRULE => RtnlSegment::NewRule(RuleSegment::read_from(header, reader)?),
+            CSegmentType::DELRULE => RtnlSegment::DelRule(RuleSegment::read_from(header, reader)?),
+            CSegmentType::GETRULE => RtnlSegment::GetRule(RuleSegment::read_from(header, reader)?),
             _ => return_errno_with_message!(Errno::EINVAL, "unsupported segment type"),
         };
 
@@ -99,11 +142,25 @@ impl ProtocolSegment for RtnlSegment {
         match self {
             RtnlSegment::NewLink(link_segment) => link_segment.write_to(writer)?,
             RtnlSegment::NewAddr(addr_segment) => addr_segment.write_to(writer)?,
+            RtnlSegment::NewRoute(route_segment) => route_segment.write_to(writer)?,
+            RtnlSegment::NewRule(rule_segment) => rule_segment.write_to(writer)?,
             RtnlSegment::Done(done_segment) => done_segment.write_to(writer)?,
             RtnlSegment::Error(error_segment) => error_segment.write_to(writer)?,
-            RtnlSegment::GetAddr(_) | RtnlSegment::GetLink(_) => {
+            RtnlSegment::GetAddr(_)
+            | RtnlSegment::GetLink(_)
+            | RtnlSegment::GetRoute(_)
+            | RtnlSegment::GetRule(_) => {
                 unreachable!("kernel should not write get requests to user space");
             }
+            RtnlSegment::DelLink(_)
+            | RtnlSegment::DelAddr(_)
+            | RtnlSegment::DelRoute(_)
+            | RtnlSegment::DelRule(_) => {
+                unreachable!("kernel should not write delete requests to user space");
+            }
+            RtnlSegment::SetLink(_) => {
+                unreachable!("kernel should not write set requests to user space");
+            }
         }
         Ok(())
     }
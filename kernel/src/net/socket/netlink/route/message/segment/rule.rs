@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::legacy::CRtGenMsg;
+use crate::{
+    net::socket::netlink::{
+        message::{SegmentBody, SegmentCommon},
+        route::message::attr::rule::RuleAttr,
+    },
+    prelude::*,
+};
+
+pub type RuleSegment = SegmentCommon<RuleSegmentBody, RuleAttr>;
+
+impl SegmentBody for RuleSegmentBody {
+    type CLegacyType = CRtGenMsg;
+    type CType = CFibRuleHdr;
+}
+
+/// `fib_rule_hdr` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/fib_rules.h#L22>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CFibRuleHdr {
+    pub family: u8,
+    /// The prefix length of the destination
+    pub dst_len: u8,
+    /// The prefix length of the source
+    pub src_len: u8,
+    /// TOS filter
+    pub tos: u8,
+    /// Routing table ID
+    pub table: u8,
+    pub _res1: u8,
+    pub _res2: u8,
+    /// Rule action
+    pub action: u8,
+    /// Flags
+    pub flags: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RuleSegmentBody {
+    pub family: i32,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    /// The ID of the routing table, or [`RtTable::COMPAT`] if the ID does not fit in a byte
+    ///
+    /// [`RtTable::COMPAT`]: super::route::RtTable::COMPAT
+    pub table: u8,
+    pub action: FrAction,
+    pub flags: FibRuleFlags,
+}
+
+impl TryFrom<CFibRuleHdr> for RuleSegmentBody {
+    type Error = Error;
+
+    fn try_from(value: CFibRuleHdr) -> Result<Self> {
+        Ok(Self {
+            family: value.family as i32,
+            dst_len: value.dst_len,
+            src_len: value.src_len,
+            tos: value.tos,
+            table: value.table,
+            action: FrAction::try_from(value.action)?,
+            flags: FibRuleFlags::from_bits_truncate(value.flags),
+        })
+    }
+}
+
+impl From<RuleSegmentBody> for CFibRuleHdr {
+    fn from(value: RuleSegmentBody) -> Self {
+        CFibRuleHdr {
+            family: value.family as u8,
+            dst_len: value.dst_len,
+            src_len: value.src_len,
+            tos: value.tos,
+            table: value.table,
+            _res1: 0,
+            _res2: 0,
+            action: value.action as _,
+            flags: value.flags.bits(),
+        }
+    }
+}
+
+/// Rule actions.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/fib_rules.h#L74>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+pub enum FrAction {
+    UNSPEC = 0,
+    /// Pass to fixed table
+    TO_TBL = 1,
+    /// Jump to another rule
+    GOTO = 2,
+    /// No operation
+    NOP = 3,
+    /// Drop without notification
+    BLACKHOLE = 6,
+    /// Drop with `ENETUNREACH`
+    UNREACHABLE = 7,
+    /// Drop with `EACCES`
+    PROHIBIT = 8,
+}
+
+bitflags! {
+    /// Rule flags.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/fib_rules.h#L10>.
+    pub struct FibRuleFlags: u32 {
+        const PERMANENT = 0x1;
+        const INVERT = 0x2;
+        const UNRESOLVED = 0x4;
+        const IIF_DETACHED = 0x8;
+        const OIF_DETACHED = 0x10;
+        const FIND_SADDR = 0x10000;
+    }
+}
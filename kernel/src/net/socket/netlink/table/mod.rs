@@ -95,7 +95,7 @@ impl ProtocolSocketTable {
         } else {
             let mut random_port = current!().pid();
             while random_port == UNSPECIFIED_PORT || self.unicast_sockets.contains(&random_port) {
-                getrandom(random_port.as_bytes_mut()).unwrap();
+                getrandom(random_port.as_bytes_mut());
             }
             random_port
         };
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Sub,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::group::{self, UEVENT_BUFFER_SIZE};
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        netlink::{table::BoundHandle, NetlinkSocketAddr},
+        util::datagram_common,
+        SendRecvFlags,
+    },
+    prelude::*,
+    process::signal::Pollee,
+    util::{MultiRead, MultiWrite},
+};
+
+pub(super) struct BoundNetlinkUevent {
+    handle: BoundHandle,
+    remote_addr: NetlinkSocketAddr,
+    receive_queue: Arc<ReceiveQueue>,
+}
+
+impl BoundNetlinkUevent {
+    pub(super) fn new(handle: BoundHandle, pollee: &Pollee) -> Self {
+        let receive_queue = Arc::new(ReceiveQueue::new(pollee.clone()));
+        group::join_groups(&handle.addr(), &receive_queue);
+
+        Self {
+            handle,
+            remote_addr: NetlinkSocketAddr::new_unspecified(),
+            receive_queue,
+        }
+    }
+}
+
+impl datagram_common::Bound for BoundNetlinkUevent {
+    type Endpoint = NetlinkSocketAddr;
+
+    fn local_endpoint(&self) -> Self::Endpoint {
+        self.handle.addr()
+    }
+
+    fn remote_endpoint(&self) -> Option<&Self::Endpoint> {
+        Some(&self.remote_addr)
+    }
+
+    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
+        self.remote_addr = *endpoint;
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: &Self::Endpoint,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        if remote.groups().is_empty() {
+            return_errno_with_message!(
+                Errno::ECONNREFUSED,
+                "sending unicast netlink uevent messages is not supported"
+            );
+        }
+
+        let len = reader.sum_lens();
+        if len > UEVENT_BUFFER_SIZE {
+            return_errno_with_message!(Errno::EMSGSIZE, "the uevent message is too large");
+        }
+        let mut bytes = vec![0u8; len];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+
+        let src = NetlinkSocketAddr::new(self.handle.port(), remote.groups());
+        group::broadcast(remote.groups(), UeventMessage::new(bytes, src));
+
+        Ok(len)
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, NetlinkSocketAddr)> {
+        // TODO: Deal with other flags. Only MSG_PEEK is handled here.
+        if !flags.sub(SendRecvFlags::MSG_PEEK).is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        self.receive_queue
+            .pop(writer, flags.contains(SendRecvFlags::MSG_PEEK))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        IoEvents::OUT | self.receive_queue.check_io_events()
+    }
+}
+
+/// A uevent message, which is a sequence of NUL-terminated strings.
+#[derive(Debug, Clone)]
+pub(super) struct UeventMessage {
+    bytes: Vec<u8>,
+    src: NetlinkSocketAddr,
+}
+
+impl UeventMessage {
+    pub(super) fn new(bytes: Vec<u8>, src: NetlinkSocketAddr) -> Self {
+        Self { bytes, src }
+    }
+
+    /// Returns the address of the sender.
+    ///
+    /// The port is zero if the message comes from the kernel.
+    pub(super) fn src(&self) -> &NetlinkSocketAddr {
+        &self.src
+    }
+}
+
+/// The queue of the messages that a netlink uevent socket receives.
+pub(super) struct ReceiveQueue {
+    messages: Mutex<VecDeque<UeventMessage>>,
+    /// Whether some messages are dropped because the queue is full.
+    has_overrun: AtomicBool,
+    pollee: Pollee,
+}
+
+/// The maximum number of the messages in a receive queue.
+const MAX_QUEUED_MESSAGES: usize = 1024;
+
+impl ReceiveQueue {
+    fn new(pollee: Pollee) -> Self {
+        Self {
+            messages: Mutex::new(VecDeque::new()),
+            has_overrun: AtomicBool::new(false),
+            pollee,
+        }
+    }
+
+    /// Pushes a message to the queue.
+    ///
+    /// If the queue is full, the message is dropped and the next receive operation will fail
+    /// with [`Errno::ENOBUFS`], as in Linux.
+    pub(super) fn push(&self, message: UeventMessage) {
+        let mut messages = self.messages.lock();
+        if messages.len() >= MAX_QUEUED_MESSAGES {
+            self.has_overrun.store(true, Ordering::Relaxed);
+            drop(messages);
+            self.pollee.notify(IoEvents::IN | IoEvents::ERR);
+            return;
+        }
+
+        messages.push_back(message);
+        drop(messages);
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Pops a message from the queue and writes it to `writer`.
+    ///
+    /// The message is truncated if `writer` is not large enough.
+    fn pop(
+        &self,
+        writer: &mut dyn MultiWrite,
+        is_peek: bool,
+    ) -> Result<(usize, NetlinkSocketAddr)> {
+        if self.has_overrun.swap(false, Ordering::Relaxed) {
+            return_errno_with_message!(Errno::ENOBUFS, "some messages have been dropped");
+        }
+
+        let mut messages = self.messages.lock();
+
+        let Some(message) = messages.front() else {
+            return_errno_with_message!(Errno::EAGAIN, "nothing to receive");
+        };
+
+        let len = writer.write(&mut VmReader::from(message.bytes.as_slice()))?;
+        let src = message.src;
+
+        if !is_peek {
+            messages.pop_front().unwrap();
+        }
+
+        Ok((len, src))
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        if self.has_overrun.load(Ordering::Relaxed) {
+            return IoEvents::IN | IoEvents::ERR;
+        }
+
+        if self.messages.lock().is_empty() {
+            IoEvents::empty()
+        } else {
+            IoEvents::IN
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The multicast groups of the kobject uevent sockets.
+
+use alloc::format;
+
+use aster_systree::{register_uevent_listener, Uevent};
+
+use super::bound::{ReceiveQueue, UeventMessage};
+use crate::{
+    net::socket::netlink::{
+        addr::{PortNum, UNSPECIFIED_PORT},
+        GroupIdSet, NetlinkSocketAddr,
+    },
+    prelude::*,
+};
+
+/// The multicast group of the uevents sent by the kernel.
+pub(super) const UEVENT_KERNEL_GROUP: u32 = 1;
+
+/// The maximum length of a uevent message.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/linux/kobject.h#L33>.
+pub(super) const UEVENT_BUFFER_SIZE: usize = 2048;
+
+/// A socket that belongs to some multicast groups.
+struct GroupMember {
+    port: PortNum,
+    groups: GroupIdSet,
+    queue: Weak<ReceiveQueue>,
+}
+
+/// The receive queues of the sockets in the multicast groups.
+static GROUP_MEMBERS: Mutex<Vec<GroupMember>> = Mutex::new(Vec::new());
+
+/// Adds the receive queue of a socket to the multicast groups that the socket is bound to.
+///
+/// The queue leaves the groups automatically when it is dropped.
+pub(super) fn join_groups(addr: &NetlinkSocketAddr, queue: &Arc<ReceiveQueue>) {
+    if addr.groups().is_empty() {
+        return;
+    }
+
+    let mut members = GROUP_MEMBERS.lock();
+    members.retain(|member| member.queue.strong_count() > 0);
+    members.push(GroupMember {
+        port: addr.port(),
+        groups: addr.groups(),
+        queue: Arc::downgrade(queue),
+    });
+}
+
+/// Sends a message to all the sockets in the given multicast groups,
+/// except the sender itself.
+pub(super) fn broadcast(groups: GroupIdSet, message: UeventMessage) {
+    let sender_port = message.src().port();
+
+    // Collect the queues first to avoid holding the lock while pushing the messages.
+    let members: Vec<_> = GROUP_MEMBERS
+        .lock()
+        .iter()
+        .filter(|member| {
+            member.groups.as_u32() & groups.as_u32() != 0
+                && (sender_port == UNSPECIFIED_PORT || member.port != sender_port)
+        })
+        .filter_map(|member| member.queue.upgrade())
+        .collect();
+
+    let Some((last, others)) = members.split_last() else {
+        return;
+    };
+    for member in others {
+        member.push(message.clone());
+    }
+    last.push(message);
+}
+
+/// Forwards the uevents of the device model to the [`UEVENT_KERNEL_GROUP`] multicast group.
+pub(in crate::net::socket::netlink) fn init() {
+    register_uevent_listener(&forward_uevent);
+}
+
+fn forward_uevent(event: &Uevent) {
+    let mut bytes = format!("{}@{}\0", event.action().as_str(), event.devpath()).into_bytes();
+    event.visit_env(|key, value| {
+        bytes.extend_from_slice(key.as_bytes());
+        bytes.push(b'=');
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+    });
+    if bytes.len() > UEVENT_BUFFER_SIZE {
+        warn!("the uevent of {} is too large", event.devpath());
+        return;
+    }
+
+    let groups = GroupIdSet::new(1 << (UEVENT_KERNEL_GROUP - 1));
+    let src = NetlinkSocketAddr::new(UNSPECIFIED_PORT, groups);
+    broadcast(groups, UeventMessage::new(bytes, src));
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{net::socket::SocketAddr, prelude::*};
+
+/// The socket address of a packet socket.
+///
+/// This is `struct sockaddr_ll` in Linux. When binding or sending, only the protocol, the
+/// interface index, and the link-layer address (for sending via `SOCK_DGRAM` sockets) are used.
+/// When receiving, all the fields describe the received frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSocketAddr {
+    /// The link-layer protocol in host byte order (e.g., [`ETH_P_IP`]).
+    pub protocol: u16,
+    /// The interface index, where zero means any interface.
+    pub ifindex: u32,
+    /// The hardware type (e.g., `ARPHRD_ETHER`).
+    pub hatype: u16,
+    /// The packet type (e.g., [`PacketType::HOST`]).
+    pub pkttype: u8,
+    /// The length of the link-layer address.
+    pub halen: u8,
+    /// The link-layer address.
+    pub addr: [u8; 8],
+}
+
+impl PacketSocketAddr {
+    /// Returns the link-layer address.
+    pub fn hardware_addr(&self) -> &[u8] {
+        &self.addr[..(self.halen as usize).min(self.addr.len())]
+    }
+}
+
+impl TryFrom<SocketAddr> for PacketSocketAddr {
+    type Error = Error;
+
+    fn try_from(value: SocketAddr) -> Result<Self> {
+        match value {
+            SocketAddr::Packet(addr) => Ok(addr),
+            _ => return_errno_with_message!(
+                Errno::EINVAL,
+                "the address is in an unsupported address family"
+            ),
+        }
+    }
+}
+
+impl From<PacketSocketAddr> for SocketAddr {
+    fn from(value: PacketSocketAddr) -> Self {
+        SocketAddr::Packet(value)
+    }
+}
+
+/// Packet types, which describe where the frames go.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_packet.h#L26>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+    /// To us
+    HOST = 0,
+    /// To all
+    BROADCAST = 1,
+    /// To group
+    MULTICAST = 2,
+    /// To someone else
+    OTHERHOST = 3,
+    /// Outgoing of any type
+    OUTGOING = 4,
+}
+
+/// Every protocol, which is used to receive all frames.
+pub const ETH_P_ALL: u16 = 0x0003;
+/// Internet Protocol version 4.
+pub const ETH_P_IP: u16 = 0x0800;
+/// Internet Protocol version 6.
+pub const ETH_P_IPV6: u16 = 0x86DD;
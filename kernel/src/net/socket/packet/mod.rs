@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module defines packet sockets (i.e., `AF_PACKET` sockets).
+//!
+//! Packet sockets send and receive raw frames at the device layer, bypassing the rest of the
+//! network stack. They are used by tools like `tcpdump` to capture the network traffic.
+//!
+//! The frames are tapped right at the network devices, so a packet socket sees both the incoming
+//! and the outgoing frames. The frames can be filtered by classic BPF programs attached with
+//! `SO_ATTACH_FILTER`, and can be received through a `TPACKET_V3` ring mapped into the user space
+//! to avoid copying them one by one.
+
+mod addr;
+mod options;
+mod ring;
+mod socket;
+mod tap;
+
+pub use addr::{PacketSocketAddr, PacketType, ETH_P_ALL, ETH_P_IP, ETH_P_IPV6};
+pub use options::{PacketStats, RxRing, Statistics, Version};
+pub use ring::{CTpacketReq3, TpacketVersion};
+pub use socket::PacketSocket;
+pub use tap::PacketTap;
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{CTpacketReq3, TpacketVersion};
+use crate::impl_socket_options;
+
+impl_socket_options!(
+    pub struct Version(TpacketVersion);
+    pub struct RxRing(CTpacketReq3);
+    pub struct Statistics(PacketStats);
+);
+
+/// The statistics of a packet socket.
+///
+/// This is `struct tpacket_stats` in Linux, or `struct tpacket_stats_v3` if `freeze_q_cnt` is
+/// present.
+#[derive(Debug, Clone, Copy)]
+pub struct PacketStats {
+    /// The number of received frames, including the dropped ones
+    pub packets: u32,
+    /// The number of dropped frames
+    pub drops: u32,
+    /// The number of times that the ring is frozen
+    pub freeze_q_cnt: Option<u32>,
+}
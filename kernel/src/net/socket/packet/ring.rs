@@ -0,0 +1,486 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Receive rings for packet sockets (i.e., `PACKET_MMAP`).
+//!
+//! Only `TPACKET_V3` rings are supported. A ring consists of blocks, and each block holds
+//! variable-length frames. The kernel fills the blocks one by one and hands over each block to
+//! the user space by setting its status to `TP_STATUS_USER`. The user space hands back a block by
+//! setting its status to `TP_STATUS_KERNEL` after consuming it.
+//!
+//! Reference: <https://www.kernel.org/doc/html/v6.13/networking/packet_mmap.html>.
+
+use core::{
+    sync::atomic::{fence, Ordering},
+    time::Duration,
+};
+
+use aster_rights::Rights;
+use aster_softirq::BottomHalfDisabled;
+use ostd::mm::{UFrame, UntypedMem};
+
+use super::PacketSocketAddr;
+use crate::{
+    events::IoEvents,
+    net::socket::SocketAddr,
+    prelude::*,
+    process::signal::Pollee,
+    time::{
+        clocks::{MonotonicClock, RealTimeClock},
+        timer::Timeout,
+        Clock, Timer,
+    },
+    util::net::socket_addr_to_c_bytes,
+    vm::vmo::{CommitFlags, Vmo, VmoOptions},
+};
+
+/// The versions of the ring formats.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_packet.h#L200>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub enum TpacketVersion {
+    V1 = 0,
+    V2 = 1,
+    V3 = 2,
+}
+
+/// The request to set up a ring.
+///
+/// This is `struct tpacket_req3` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/if_packet.h#L277>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CTpacketReq3 {
+    /// Minimal size of contiguous block
+    pub block_size: u32,
+    /// Number of blocks
+    pub block_nr: u32,
+    /// Size of frame
+    pub frame_size: u32,
+    /// Total number of frames
+    pub frame_nr: u32,
+    /// Timeout in msecs
+    pub retire_blk_tov: u32,
+    /// Offset to private data area
+    pub sizeof_priv: u32,
+    /// Feature request word
+    pub feature_req_word: u32,
+}
+
+/// The header of a block (i.e., `struct tpacket_block_desc` with `struct tpacket_hdr_v1`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CBlockDesc {
+    version: u32,
+    offset_to_priv: u32,
+    block_status: u32,
+    num_pkts: u32,
+    offset_to_first_pkt: u32,
+    blk_len: u32,
+    seq_num: u64,
+    ts_first_pkt: CBlockTimestamp,
+    ts_last_pkt: CBlockTimestamp,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CBlockTimestamp {
+    sec: u32,
+    nsec: u32,
+}
+
+/// The header of a frame (i.e., `struct tpacket3_hdr`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CTpacket3Hdr {
+    next_offset: u32,
+    sec: u32,
+    nsec: u32,
+    snaplen: u32,
+    len: u32,
+    status: u32,
+    mac: u16,
+    net: u16,
+    rxhash: u32,
+    vlan_tci: u32,
+    vlan_tpid: u16,
+    padding: u16,
+    tp_padding: [u8; 8],
+}
+
+const TP_STATUS_KERNEL: u32 = 0;
+const TP_STATUS_USER: u32 = 1 << 0;
+const TP_STATUS_BLK_TMO: u32 = 1 << 5;
+
+const TPACKET_ALIGNMENT: usize = 16;
+/// The size of the frame header followed by the socket address (i.e., `TPACKET3_HDRLEN`).
+const TPACKET3_HDRLEN: usize = size_of::<CTpacket3Hdr>().next_multiple_of(TPACKET_ALIGNMENT) + 20;
+
+/// The block header size, after which the private area starts.
+const BLK_HDR_LEN: usize = size_of::<CBlockDesc>();
+
+/// The maximum size of a ring.
+const MAX_RING_SIZE: usize = 64 * 1024 * 1024;
+
+/// The default timeout to retire a block, which Linux uses if the link speed is unknown.
+const DEFAULT_RETIRE_TIMEOUT: Duration = Duration::from_millis(8);
+
+/// A `TPACKET_V3` receive ring.
+pub(super) struct Tpacket3Ring {
+    mem: RingMem,
+    block_size: usize,
+    nr_blocks: usize,
+    first_pkt_offset: usize,
+    state: SpinLock<RingState, BottomHalfDisabled>,
+    retire_timer: Arc<Timer>,
+}
+
+struct RingState {
+    /// The index of the current block.
+    cur_block: usize,
+    /// Whether the current block is owned by the kernel.
+    ///
+    /// If this is false, the ring is frozen because the user space has not handed back the
+    /// current block yet.
+    is_open: bool,
+    /// The offset of the next frame in the current block.
+    offset: usize,
+    /// The offset of the last frame in the current block.
+    last_pkt_offset: Option<usize>,
+    nr_pkts: u32,
+    seq_num: u64,
+    ts_first_pkt: Duration,
+    ts_last_pkt: Duration,
+    /// The number of times that the ring is frozen.
+    freeze_count: u32,
+}
+
+impl Tpacket3Ring {
+    /// Creates a ring after validating the request.
+    ///
+    /// The pollee will be notified when blocks are handed over to the user space.
+    pub(super) fn new(req: &CTpacketReq3, pollee: Pollee) -> Result<Arc<Self>> {
+        let block_size = req.block_size as usize;
+        let nr_blocks = req.block_nr as usize;
+        let frame_size = req.frame_size as usize;
+
+        if block_size == 0 || block_size % PAGE_SIZE != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the block size is invalid");
+        }
+        if frame_size < TPACKET3_HDRLEN || frame_size % TPACKET_ALIGNMENT != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the frame size is invalid");
+        }
+        let frames_per_block = block_size / frame_size;
+        if frames_per_block == 0
+            || nr_blocks == 0
+            || frames_per_block.checked_mul(nr_blocks) != Some(req.frame_nr as usize)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the number of frames is invalid");
+        }
+
+        let first_pkt_offset = BLK_HDR_LEN
+            .checked_add((req.sizeof_priv as usize).next_multiple_of(8))
+            .filter(|offset| *offset < block_size)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the private area is too large"))?;
+
+        let ring_size = block_size
+            .checked_mul(nr_blocks)
+            .filter(|size| *size <= MAX_RING_SIZE)
+            .ok_or_else(|| Error::with_message(Errno::ENOMEM, "the ring is too large"))?;
+
+        let retire_timeout = match req.retire_blk_tov {
+            0 => DEFAULT_RETIRE_TIMEOUT,
+            tov => Duration::from_millis(tov as u64),
+        };
+
+        let mem = RingMem::new(ring_size)?;
+
+        let ring = Arc::new_cyclic(|weak_ring: &Weak<Tpacket3Ring>| {
+            let weak_ring = weak_ring.clone();
+            let retire_timer = MonotonicClock::timer_manager().create_timer(move || {
+                let Some(ring) = weak_ring.upgrade() else {
+                    return;
+                };
+                if ring.retire_on_timeout() {
+                    pollee.notify(IoEvents::IN);
+                }
+            });
+
+            Self {
+                mem,
+                block_size,
+                nr_blocks,
+                first_pkt_offset,
+                state: SpinLock::new(RingState {
+                    cur_block: 0,
+                    is_open: false,
+                    offset: 0,
+                    last_pkt_offset: None,
+                    nr_pkts: 0,
+                    seq_num: 0,
+                    ts_first_pkt: Duration::ZERO,
+                    ts_last_pkt: Duration::ZERO,
+                    freeze_count: 0,
+                }),
+                retire_timer,
+            }
+        });
+
+        ring.open_block(&mut ring.state.lock());
+
+        ring.retire_timer.set_interval(retire_timeout);
+        ring.retire_timer
+            .set_timeout(Timeout::After(retire_timeout));
+
+        Ok(ring)
+    }
+
+    /// Returns the VMO that backs the ring, which can be mapped to the user space.
+    pub(super) fn vmo(&self) -> &Vmo<Rights> {
+        &self.mem.vmo
+    }
+
+    /// Writes a frame to the ring.
+    ///
+    /// `data` starts with the link-layer header if `mac_len` is not zero, or with the
+    /// network-layer header otherwise. The data will be truncated if it does not fit in a block.
+    ///
+    /// The returned [`PushResult`] tells whether the frame is written and whether a block is
+    /// handed over to the user space.
+    pub(super) fn push(
+        &self,
+        data: &[u8],
+        mac_len: usize,
+        orig_len: usize,
+        addr: &PacketSocketAddr,
+    ) -> PushResult {
+        let (mac_offset, net_offset) = if mac_len == 0 {
+            let offset = TPACKET3_HDRLEN.next_multiple_of(TPACKET_ALIGNMENT) + 16;
+            (offset, offset)
+        } else {
+            let net_offset =
+                (TPACKET3_HDRLEN + mac_len.max(16)).next_multiple_of(TPACKET_ALIGNMENT);
+            (net_offset - mac_len, net_offset)
+        };
+
+        let max_snaplen = (self.block_size - self.first_pkt_offset).saturating_sub(mac_offset);
+        let snaplen = data.len().min(max_snaplen);
+        let total_len = (mac_offset + snaplen).next_multiple_of(TPACKET_ALIGNMENT);
+
+        let mut state = self.state.lock();
+
+        let mut is_handed_over = false;
+        if state.is_open && state.nr_pkts != 0 && state.offset + total_len > self.block_size {
+            self.close_block(&mut state, 0);
+            is_handed_over = true;
+        }
+        if !state.is_open || state.offset + total_len > self.block_size {
+            return PushResult {
+                is_written: false,
+                is_handed_over,
+            };
+        }
+
+        let now = RealTimeClock::get().read_time();
+        if state.nr_pkts == 0 {
+            state.ts_first_pkt = now;
+        }
+        state.ts_last_pkt = now;
+
+        let pkt_offset = state.cur_block * self.block_size + state.offset;
+        let header = CTpacket3Hdr {
+            next_offset: total_len as u32,
+            sec: now.as_secs() as u32,
+            nsec: now.subsec_nanos(),
+            snaplen: snaplen as u32,
+            len: orig_len as u32,
+            status: TP_STATUS_USER,
+            mac: mac_offset as u16,
+            net: net_offset as u16,
+            rxhash: 0,
+            vlan_tci: 0,
+            vlan_tpid: 0,
+            padding: 0,
+            tp_padding: [0; 8],
+        };
+        self.mem.write_bytes(pkt_offset, header.as_bytes());
+        let addr_bytes = socket_addr_to_c_bytes(&SocketAddr::Packet(*addr));
+        self.mem.write_bytes(
+            pkt_offset + size_of::<CTpacket3Hdr>().next_multiple_of(TPACKET_ALIGNMENT),
+            &addr_bytes,
+        );
+        self.mem
+            .write_bytes(pkt_offset + mac_offset, &data[..snaplen]);
+
+        state.last_pkt_offset = Some(state.offset);
+        state.offset += total_len;
+        state.nr_pkts += 1;
+
+        PushResult {
+            is_written: true,
+            is_handed_over,
+        }
+    }
+
+    /// Returns whether any blocks are owned by the user space.
+    pub(super) fn has_user_blocks(&self) -> bool {
+        (0..self.nr_blocks).any(|block| self.block_status(block) != TP_STATUS_KERNEL)
+    }
+
+    /// Returns the number of times that the ring is frozen and resets it.
+    pub(super) fn take_freeze_count(&self) -> u32 {
+        core::mem::take(&mut self.state.lock().freeze_count)
+    }
+
+    /// Retires the current block if it is not empty, or tries to thaw the ring if it is frozen.
+    ///
+    /// Returns whether a block is handed over to the user space.
+    fn retire_on_timeout(&self) -> bool {
+        let mut state = self.state.lock();
+
+        if !state.is_open {
+            self.open_block(&mut state);
+            return false;
+        }
+        if state.nr_pkts == 0 {
+            return false;
+        }
+
+        self.close_block(&mut state, TP_STATUS_BLK_TMO);
+        true
+    }
+
+    /// Hands over the current block to the user space and moves to the next block.
+    fn close_block(&self, state: &mut RingState, status_flags: u32) {
+        let block_offset = state.cur_block * self.block_size;
+
+        if let Some(last_pkt_offset) = state.last_pkt_offset {
+            // Like Linux, the last frame in a block has no next frame.
+            self.mem.write_once(block_offset + last_pkt_offset, 0);
+        }
+
+        let desc = CBlockDesc {
+            version: TpacketVersion::V3 as u32,
+            offset_to_priv: BLK_HDR_LEN as u32,
+            block_status: TP_STATUS_KERNEL,
+            num_pkts: state.nr_pkts,
+            offset_to_first_pkt: self.first_pkt_offset as u32,
+            blk_len: state.offset as u32,
+            seq_num: state.seq_num,
+            ts_first_pkt: CBlockTimestamp::from(state.ts_first_pkt),
+            ts_last_pkt: CBlockTimestamp::from(state.ts_last_pkt),
+        };
+        self.mem.write_bytes(block_offset, desc.as_bytes());
+
+        // The user space must see the frames once it sees the new status.
+        fence(Ordering::Release);
+        self.mem.write_once(
+            block_offset + core::mem::offset_of!(CBlockDesc, block_status),
+            TP_STATUS_USER | status_flags,
+        );
+
+        state.cur_block = (state.cur_block + 1) % self.nr_blocks;
+        state.is_open = false;
+        state.nr_pkts = 0;
+        self.open_block(state);
+        if !state.is_open {
+            state.freeze_count += 1;
+        }
+    }
+
+    /// Tries to take back the current block from the user space.
+    fn open_block(&self, state: &mut RingState) {
+        debug_assert!(!state.is_open);
+
+        if self.block_status(state.cur_block) != TP_STATUS_KERNEL {
+            // The user space is still consuming the block, so the ring is frozen.
+            return;
+        }
+
+        // The user space must finish reading the frames before handing back the block.
+        fence(Ordering::Acquire);
+
+        state.is_open = true;
+        state.offset = self.first_pkt_offset;
+        state.last_pkt_offset = None;
+        state.nr_pkts = 0;
+        state.seq_num += 1;
+    }
+
+    fn block_status(&self, block: usize) -> u32 {
+        self.mem
+            .read_once(block * self.block_size + core::mem::offset_of!(CBlockDesc, block_status))
+    }
+}
+
+impl Drop for Tpacket3Ring {
+    fn drop(&mut self) {
+        self.retire_timer.cancel();
+    }
+}
+
+/// The result of [`Tpacket3Ring::push`].
+pub(super) struct PushResult {
+    /// Whether the frame is written to the ring.
+    pub(super) is_written: bool,
+    /// Whether blocks are handed over to the user space.
+    pub(super) is_handed_over: bool,
+}
+
+impl From<Duration> for CBlockTimestamp {
+    fn from(value: Duration) -> Self {
+        Self {
+            sec: value.as_secs() as u32,
+            nsec: value.subsec_nanos(),
+        }
+    }
+}
+
+/// The memory that is shared between the kernel and the user space.
+struct RingMem {
+    vmo: Vmo<Rights>,
+    frames: Vec<UFrame>,
+}
+
+impl RingMem {
+    fn new(size: usize) -> Result<Self> {
+        let nr_pages = size.div_ceil(PAGE_SIZE);
+        let vmo = VmoOptions::<Rights>::new(nr_pages * PAGE_SIZE).alloc()?;
+        let frames = (0..nr_pages)
+            .map(|page_idx| vmo.commit_on(page_idx, CommitFlags::empty()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { vmo, frames })
+    }
+
+    // The `u32` values accessed below never cross page boundaries, since they are naturally
+    // aligned.
+
+    fn read_once(&self, offset: usize) -> u32 {
+        let mut reader = self.frames[offset / PAGE_SIZE].reader();
+        reader.skip(offset % PAGE_SIZE).read_once().unwrap()
+    }
+
+    fn write_once(&self, offset: usize, new_val: u32) {
+        let mut writer = self.frames[offset / PAGE_SIZE].writer();
+        writer
+            .skip(offset % PAGE_SIZE)
+            .write_once(&new_val)
+            .unwrap();
+    }
+
+    fn write_bytes(&self, mut offset: usize, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let page_offset = offset % PAGE_SIZE;
+            let len = bytes.len().min(PAGE_SIZE - page_offset);
+
+            let mut writer = self.frames[offset / PAGE_SIZE].writer();
+            writer
+                .skip(page_offset)
+                .write(&mut VmReader::from(&bytes[..len]));
+
+            offset += len;
+            bytes = &bytes[len..];
+        }
+    }
+}
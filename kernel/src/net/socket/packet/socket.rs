@@ -0,0 +1,506 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Sub,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use aster_bigtcp::iface::FrameDirection;
+use aster_rights::Rights;
+use aster_softirq::BottomHalfDisabled;
+
+use super::{
+    options::{PacketStats, RxRing, Statistics, Version},
+    ring::Tpacket3Ring,
+    tap::{self, TappedFrame, ETH_HLEN},
+    CTpacketReq3, PacketSocketAddr, TpacketVersion, ETH_P_ALL,
+};
+use crate::{
+    events::IoEvents,
+    match_sock_option_mut, match_sock_option_ref,
+    net::{
+        iface::{iter_all_ifaces, Iface},
+        socket::{
+            options::{AttachFilter, DetachFilter, SocketOption},
+            private::SocketPrivate,
+            util::filter::FilterInput,
+            MessageHeader, SendRecvFlags, Socket, SocketAddr, SocketFilter,
+        },
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+    util::{MultiRead, MultiWrite},
+    vm::vmo::Vmo,
+};
+
+/// A packet socket (i.e., an `AF_PACKET` socket).
+///
+/// A packet socket receives the frames that go through the network devices, and sends frames to
+/// the network devices directly. The frames include the link-layer headers if the socket is a
+/// `SOCK_RAW` socket, or start with the network-layer headers if the socket is a `SOCK_DGRAM`
+/// socket.
+pub struct PacketSocket {
+    is_raw: bool,
+    state: SpinLock<State, BottomHalfDisabled>,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+}
+
+struct State {
+    /// The protocol to receive, where zero means nothing to receive.
+    protocol: u16,
+    /// The index of the iface to receive from, where zero means any iface.
+    ifindex: u32,
+    filter: Option<SocketFilter>,
+    version: TpacketVersion,
+    rx_ring: Option<Arc<Tpacket3Ring>>,
+    receive_queue: VecDeque<ReceivedFrame>,
+    queued_bytes: usize,
+    nr_packets: u32,
+    nr_drops: u32,
+}
+
+#[derive(Clone)]
+struct ReceivedFrame {
+    data: Vec<u8>,
+    src: PacketSocketAddr,
+}
+
+/// The maximum number of bytes in a receive queue.
+///
+/// This is the default value of `/proc/sys/net/core/rmem_default` in Linux.
+const RECV_BUF_LEN: usize = 212992;
+
+impl PacketSocket {
+    /// Creates a packet socket that receives the frames of the protocol.
+    ///
+    /// `protocol` is in the host byte order. Like Linux, the socket starts receiving frames
+    /// immediately, unless `protocol` is zero.
+    pub fn new(is_raw: bool, protocol: u16, is_nonblocking: bool) -> Result<Arc<Self>> {
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_RAW) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "creating packet sockets requires CAP_NET_RAW"
+            );
+        }
+
+        let socket = Arc::new(Self {
+            is_raw,
+            state: SpinLock::new(State {
+                protocol,
+                ifindex: 0,
+                filter: None,
+                version: TpacketVersion::V1,
+                rx_ring: None,
+                receive_queue: VecDeque::new(),
+                queued_bytes: 0,
+                nr_packets: 0,
+                nr_drops: 0,
+            }),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee: Pollee::new(),
+        });
+        tap::register(Arc::downgrade(&socket));
+
+        Ok(socket)
+    }
+
+    /// Delivers a tapped frame to the socket if the socket wants it.
+    pub(super) fn deliver(&self, tapped_frame: &TappedFrame) {
+        let mut state = self.state.lock();
+
+        let wants_protocol = match state.protocol {
+            ETH_P_ALL => true,
+            0 => false,
+            // Like Linux, only the sockets receiving all protocols see the outgoing frames.
+            protocol => {
+                protocol == tapped_frame.protocol
+                    && tapped_frame.direction == FrameDirection::Incoming
+            }
+        };
+        if !wants_protocol || (state.ifindex != 0 && state.ifindex != tapped_frame.ifindex) {
+            return;
+        }
+
+        let data_offset = if self.is_raw { 0 } else { ETH_HLEN };
+        let orig_len = tapped_frame.frame.len() - data_offset;
+
+        let snaplen = match state.filter.as_ref() {
+            Some(filter) => {
+                let input = FilterInput {
+                    frame: tapped_frame.frame,
+                    data_offset,
+                    net_offset: ETH_HLEN,
+                    protocol: tapped_frame.protocol,
+                    pkttype: tapped_frame.pkttype as u8,
+                    ifindex: tapped_frame.ifindex,
+                    hatype: tapped_frame.hatype,
+                };
+                (filter.run(&input) as usize).min(orig_len)
+            }
+            None => orig_len,
+        };
+        if snaplen == 0 {
+            return;
+        }
+        let data = &tapped_frame.frame[data_offset..data_offset + snaplen];
+
+        let mut addr = [0; 8];
+        addr[..6].copy_from_slice(tapped_frame.src_addr());
+        let src = PacketSocketAddr {
+            protocol: tapped_frame.protocol,
+            ifindex: tapped_frame.ifindex,
+            hatype: tapped_frame.hatype,
+            pkttype: tapped_frame.pkttype as u8,
+            halen: 6,
+            addr,
+        };
+
+        let should_notify = if let Some(rx_ring) = state.rx_ring.as_ref() {
+            let mac_len = if self.is_raw { ETH_HLEN } else { 0 };
+            let result = rx_ring.push(data, mac_len, orig_len, &src);
+            if result.is_written {
+                state.nr_packets += 1;
+            } else {
+                state.nr_drops += 1;
+            }
+            result.is_handed_over
+        } else if state.queued_bytes + snaplen > RECV_BUF_LEN {
+            state.nr_drops += 1;
+            false
+        } else {
+            state.receive_queue.push_back(ReceivedFrame {
+                data: data.to_vec(),
+                src,
+            });
+            state.queued_bytes += snaplen;
+            state.nr_packets += 1;
+            true
+        };
+        drop(state);
+
+        if should_notify {
+            self.pollee.notify(IoEvents::IN);
+        }
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, PacketSocketAddr)> {
+        // TODO: Deal with other flags. Only MSG_PEEK and MSG_TRUNC are handled here.
+        if !flags
+            .sub(SendRecvFlags::MSG_PEEK | SendRecvFlags::MSG_TRUNC)
+            .is_all_supported()
+        {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let frame = {
+            let mut state = self.state.lock();
+            if flags.contains(SendRecvFlags::MSG_PEEK) {
+                state.receive_queue.front().cloned()
+            } else {
+                let frame = state.receive_queue.pop_front();
+                if let Some(frame) = frame.as_ref() {
+                    state.queued_bytes -= frame.data.len();
+                }
+                frame
+            }
+        };
+        let Some(frame) = frame else {
+            return_errno_with_message!(Errno::EAGAIN, "nothing to receive");
+        };
+        self.pollee.invalidate();
+
+        let copied_len = writer.write(&mut VmReader::from(frame.data.as_slice()))?;
+
+        // Like Linux, the real length of the frame is returned if `MSG_TRUNC` is specified.
+        let len = if flags.contains(SendRecvFlags::MSG_TRUNC) {
+            frame.data.len()
+        } else {
+            copied_len
+        };
+
+        Ok((len, frame.src))
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        dst: Option<&PacketSocketAddr>,
+    ) -> Result<usize> {
+        let (ifindex, protocol) = match dst {
+            Some(dst) => (dst.ifindex, dst.protocol),
+            None => {
+                let state = self.state.lock();
+                (state.ifindex, state.protocol)
+            }
+        };
+        let Some(iface) = find_iface(ifindex) else {
+            return_errno_with_message!(Errno::ENXIO, "the interface to send to is not specified");
+        };
+
+        let len = reader.sum_lens();
+        let header_len = if self.is_raw {
+            if len < ETH_HLEN {
+                return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+            }
+            ETH_HLEN
+        } else {
+            0
+        };
+        if len - header_len > iface.mtu() {
+            return_errno_with_message!(Errno::EMSGSIZE, "the frame is too long");
+        }
+
+        let mut frame = Vec::with_capacity(ETH_HLEN + len);
+        if !self.is_raw {
+            let dst_addr = dst
+                .map(|dst| dst.hardware_addr())
+                .filter(|addr| addr.len() >= 6)
+                .map_or([0; 6], |addr| addr[..6].try_into().unwrap());
+            let src_addr = iface.ether_addr().map_or([0; 6], |addr| addr.0);
+            frame.extend_from_slice(&dst_addr);
+            frame.extend_from_slice(&src_addr);
+            frame.extend_from_slice(&protocol.to_be_bytes());
+        }
+        let header_end = frame.len();
+        frame.resize(header_end + len, 0);
+        reader.read(&mut VmWriter::from(&mut frame[header_end..]))?;
+
+        // Ifaces without Ethernet headers (e.g., the loopback iface) expect the frames to start
+        // with the IP headers.
+        let frame_to_send = if iface.ether_addr().is_some() {
+            frame.as_slice()
+        } else {
+            &frame[ETH_HLEN..]
+        };
+        if !iface.send_frame(frame_to_send) {
+            return_errno_with_message!(Errno::ENOBUFS, "the device is busy");
+        }
+        iface.poll();
+
+        Ok(len)
+    }
+
+    fn set_rx_ring(&self, req: &CTpacketReq3) -> Result<()> {
+        if req.block_nr == 0 {
+            let old_rx_ring = self.state.lock().rx_ring.take();
+            drop(old_rx_ring);
+            return Ok(());
+        }
+
+        if self.state.lock().version != TpacketVersion::V3 {
+            return_errno_with_message!(Errno::EINVAL, "only TPACKET_V3 rings are supported");
+        }
+
+        let rx_ring = Tpacket3Ring::new(req, self.pollee.clone())?;
+
+        let mut state = self.state.lock();
+        if state.rx_ring.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the ring has been set up");
+        }
+        state.rx_ring = Some(rx_ring);
+
+        Ok(())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let state = self.state.lock();
+
+        let has_frames = match state.rx_ring.as_ref() {
+            Some(rx_ring) => rx_ring.has_user_blocks(),
+            None => !state.receive_queue.is_empty(),
+        };
+
+        if has_frames {
+            IoEvents::IN | IoEvents::OUT
+        } else {
+            IoEvents::OUT
+        }
+    }
+}
+
+impl Socket for PacketSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let addr = PacketSocketAddr::try_from(socket_addr)?;
+
+        if addr.ifindex != 0 && find_iface(addr.ifindex).is_none() {
+            return_errno_with_message!(Errno::ENODEV, "the interface does not exist");
+        }
+
+        let mut state = self.state.lock();
+        state.ifindex = addr.ifindex;
+        // Like Linux, a zero protocol keeps the current protocol.
+        if addr.protocol != 0 {
+            state.protocol = addr.protocol;
+        }
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let (ifindex, protocol) = {
+            let state = self.state.lock();
+            (state.ifindex, state.protocol)
+        };
+
+        let mut addr = PacketSocketAddr {
+            protocol,
+            ifindex,
+            hatype: 0,
+            pkttype: 0,
+            halen: 0,
+            addr: [0; 8],
+        };
+        if let Some(iface) = find_iface(ifindex) {
+            addr.hatype = iface.type_() as u16;
+            addr.halen = 6;
+            if let Some(ether_addr) = iface.ether_addr() {
+                addr.addr[..6].copy_from_slice(ether_addr.as_bytes());
+            }
+        }
+
+        Ok(addr.into())
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            version: Version => {
+                version.set(self.state.lock().version);
+            },
+            statistics: Statistics => {
+                let mut state = self.state.lock();
+                // Like Linux, the statistics are reset after being read.
+                let nr_packets = core::mem::take(&mut state.nr_packets);
+                let nr_drops = core::mem::take(&mut state.nr_drops);
+                let freeze_q_cnt = (state.version == TpacketVersion::V3).then(|| {
+                    state
+                        .rx_ring
+                        .as_ref()
+                        .map_or(0, |rx_ring| rx_ring.take_freeze_count())
+                });
+                statistics.set(PacketStats {
+                    packets: nr_packets.wrapping_add(nr_drops),
+                    drops: nr_drops,
+                    freeze_q_cnt,
+                });
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            attach_filter: AttachFilter => {
+                let filter = attach_filter.get().unwrap();
+                self.state.lock().filter = Some(filter.clone());
+            },
+            _detach_filter: DetachFilter => {
+                if self.state.lock().filter.take().is_none() {
+                    return_errno_with_message!(Errno::ENOENT, "no filter is attached");
+                }
+            },
+            version: Version => {
+                let mut state = self.state.lock();
+                if state.rx_ring.is_some() {
+                    return_errno_with_message!(Errno::EBUSY, "the ring has been set up");
+                }
+                state.version = *version.get().unwrap();
+            },
+            rx_ring: RxRing => {
+                self.set_rx_ring(rx_ring.get().unwrap())?;
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to set is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
+        let MessageHeader {
+            addr,
+            control_messages,
+            ..
+        } = message_header;
+
+        let dst = match addr {
+            None => None,
+            Some(addr) => Some(PacketSocketAddr::try_from(addr)?),
+        };
+
+        if !control_messages.is_empty() {
+            // TODO: Support sending control message
+            warn!("sending control message is not supported");
+        }
+
+        self.try_send(reader, dst.as_ref())
+    }
+
+    fn recvmsg(
+        &self,
+        writers: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        let (received_len, addr) =
+            self.block_on_msg(flags, IoEvents::IN, || self.try_recv(writers, flags))?;
+
+        let message_header = MessageHeader::new(Some(addr.into()), Vec::new());
+
+        Ok((received_len, message_header))
+    }
+
+    fn mmap_vmo(&self, offset: usize) -> Result<(Vmo<Rights>, usize)> {
+        if offset != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the ring must be mapped at offset zero");
+        }
+
+        let Some(rx_ring) = self.state.lock().rx_ring.clone() else {
+            return_errno_with_message!(Errno::EINVAL, "the ring has not been set up");
+        };
+
+        Ok((rx_ring.vmo().dup()?, 0))
+    }
+}
+
+impl SocketPrivate for PacketSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.is_nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Pollable for PacketSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl Drop for PacketSocket {
+    fn drop(&mut self) {
+        tap::unregister_dropped();
+    }
+}
+
+fn find_iface(ifindex: u32) -> Option<Arc<Iface>> {
+    iter_all_ifaces().find(|iface| iface.index() == ifindex)
+}
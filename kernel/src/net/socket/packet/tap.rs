@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::{
+    device::Medium,
+    iface::{FrameDirection, FrameTap},
+    wire::EthernetAddress,
+};
+use aster_softirq::BottomHalfDisabled;
+
+use super::{socket::PacketSocket, PacketType, ETH_P_IP, ETH_P_IPV6};
+use crate::{net::iface::iter_all_ifaces, prelude::*};
+
+/// The length of the Ethernet header (i.e., `ETH_HLEN`).
+pub(super) const ETH_HLEN: usize = 14;
+
+/// The packet sockets that receive the tapped frames.
+static PACKET_SOCKETS: RwLock<Vec<Weak<PacketSocket>>, BottomHalfDisabled> =
+    RwLock::new(Vec::new());
+
+/// Registers a packet socket so that it receives the tapped frames.
+pub(super) fn register(socket: Weak<PacketSocket>) {
+    PACKET_SOCKETS.write().push(socket);
+}
+
+/// Unregisters the packet sockets that have been dropped.
+pub(super) fn unregister_dropped() {
+    PACKET_SOCKETS
+        .write()
+        .retain(|socket| socket.strong_count() > 0);
+}
+
+/// A frame that is tapped at the device layer.
+pub(super) struct TappedFrame<'a> {
+    /// The frame, which always starts with an Ethernet header.
+    pub(super) frame: &'a [u8],
+    pub(super) direction: FrameDirection,
+    /// The link-layer protocol, taken from the Ethernet header.
+    pub(super) protocol: u16,
+    pub(super) ifindex: u32,
+    pub(super) hatype: u16,
+    pub(super) pkttype: PacketType,
+}
+
+impl TappedFrame<'_> {
+    /// Returns the source address in the Ethernet header.
+    pub(super) fn src_addr(&self) -> &[u8] {
+        &self.frame[6..12]
+    }
+}
+
+/// The tap that passes the frames to the packet sockets.
+pub struct PacketTap;
+
+impl FrameTap for PacketTap {
+    fn tap(iface_index: u32, medium: Medium, direction: FrameDirection, frame: &[u8]) {
+        // The sockets are collected before delivering the frame, since a socket may be dropped
+        // during the delivery, which will lock the list to unregister the socket.
+        let sockets: Vec<Arc<PacketSocket>> = {
+            let sockets = PACKET_SOCKETS.read();
+            if sockets.is_empty() {
+                return;
+            }
+            sockets.iter().filter_map(Weak::upgrade).collect()
+        };
+
+        let Some(iface) = iter_all_ifaces().find(|iface| iface.index() == iface_index) else {
+            return;
+        };
+
+        // Like Linux, frames on ifaces without Ethernet headers (e.g., the loopback iface) are
+        // presented with Ethernet headers whose addresses are all zeros.
+        let ether_frame;
+        let frame = match medium {
+            Medium::Ethernet => frame,
+            Medium::Ip => {
+                let protocol = match frame.first().map(|byte| byte >> 4) {
+                    Some(4) => ETH_P_IP,
+                    Some(6) => ETH_P_IPV6,
+                    _ => return,
+                };
+                ether_frame = {
+                    let mut bytes = Vec::with_capacity(ETH_HLEN + frame.len());
+                    bytes.extend_from_slice(&[0; 12]);
+                    bytes.extend_from_slice(&protocol.to_be_bytes());
+                    bytes.extend_from_slice(frame);
+                    bytes
+                };
+                ether_frame.as_slice()
+            }
+        };
+        if frame.len() < ETH_HLEN {
+            return;
+        }
+
+        let pkttype = match direction {
+            FrameDirection::Outgoing => PacketType::OUTGOING,
+            FrameDirection::Incoming => {
+                let dst_addr = EthernetAddress::from_bytes(&frame[0..6]);
+                if iface
+                    .ether_addr()
+                    .is_none_or(|ether_addr| ether_addr == dst_addr)
+                {
+                    PacketType::HOST
+                } else if dst_addr.is_broadcast() {
+                    PacketType::BROADCAST
+                } else if dst_addr.is_multicast() {
+                    PacketType::MULTICAST
+                } else {
+                    PacketType::OTHERHOST
+                }
+            }
+        };
+
+        let tapped_frame = TappedFrame {
+            frame,
+            direction,
+            protocol: u16::from_be_bytes([frame[12], frame[13]]),
+            ifindex: iface_index,
+            hatype: iface.type_() as u16,
+            pkttype,
+        };
+
+        for socket in sockets.iter() {
+            socket.deliver(&tapped_frame);
+        }
+    }
+}
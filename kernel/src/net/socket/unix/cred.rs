@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread, Pid},
+};
+
+/// The credentials of a process (i.e., `struct ucred`).
+///
+/// The credentials are passed with `SCM_CREDENTIALS` control messages and reported by the
+/// `SO_PEERCRED` socket option.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/socket.h#L173>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, PartialEq, Eq)]
+pub struct CUserCred {
+    pub pid: Pid,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl CUserCred {
+    /// Returns the credentials reported for a socket that has no peer.
+    pub(super) const fn unknown() -> Self {
+        Self {
+            pid: 0,
+            uid: u32::MAX,
+            gid: u32::MAX,
+        }
+    }
+
+    /// Returns the credentials that are sent if the sender does not specify them explicitly.
+    ///
+    /// Like Linux, the real user and group IDs of the current thread are used.
+    pub(super) fn current_real() -> Self {
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+
+        Self {
+            pid: current!().pid(),
+            uid: credentials.ruid().into(),
+            gid: credentials.rgid().into(),
+        }
+    }
+
+    /// Returns the credentials that will be seen by the peer via `SO_PEERCRED`.
+    ///
+    /// Like Linux, the effective user and group IDs of the current thread are used.
+    pub(super) fn current_effective() -> Self {
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+
+        Self {
+            pid: current!().pid(),
+            uid: credentials.euid().into(),
+            gid: credentials.egid().into(),
+        }
+    }
+
+    /// Checks whether the current thread is allowed to send the credentials.
+    ///
+    /// A thread can only send its own process ID, user IDs, and group IDs, unless it has the
+    /// corresponding capabilities.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/scm.c#L55>.
+    pub(super) fn check_sendable(&self, ctx: &Context) -> Result<()> {
+        let credentials = ctx.posix_thread.credentials();
+        let capset = credentials.effective_capset();
+
+        let is_pid_ok = self.pid == ctx.process.pid() || capset.contains(CapSet::SYS_ADMIN);
+        let is_uid_ok = [credentials.ruid(), credentials.euid(), credentials.suid()]
+            .into_iter()
+            .any(|uid| u32::from(uid) == self.uid)
+            || capset.contains(CapSet::SETUID);
+        let is_gid_ok = [credentials.rgid(), credentials.egid(), credentials.sgid()]
+            .into_iter()
+            .any(|gid| u32::from(gid) == self.gid)
+            || capset.contains(CapSet::SETGID);
+
+        if !is_pid_ok || !is_uid_ok || !is_gid_ok {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "the credentials cannot be sent by the current thread"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use super::cred::CUserCred;
+use crate::{
+    fs::{file_handle::FileLike, file_table::FdFlags},
+    net::socket::{
+        util::{write_control_message, CControlHeader},
+        SendRecvFlags,
+    },
+    prelude::*,
+    util::net::CSocketOptionLevel,
+};
+
+/// A control message of UNIX domain sockets.
+#[derive(Debug)]
+pub enum UnixControlMessage {
+    /// Files passed with `SCM_RIGHTS`.
+    Files(Vec<Arc<dyn FileLike>>),
+    /// Credentials passed with `SCM_CREDENTIALS`.
+    Credentials(CUserCred),
+}
+
+/// The types of socket-level control messages.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/socket.h#L161>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+enum CControlType {
+    SCM_RIGHTS = 1,
+    SCM_CREDENTIALS = 2,
+}
+
+/// The maximum number of files that can be passed in one message (i.e., `SCM_MAX_FD`).
+const MAX_NR_FILES: usize = 253;
+
+impl UnixControlMessage {
+    /// Reads the payload of a socket-level control message from the reader.
+    ///
+    /// Returns `None` if the control message carries nothing.
+    pub(in crate::net) fn read_from(
+        header: &CControlHeader,
+        reader: &mut VmReader,
+        ctx: &Context,
+    ) -> Result<Option<Self>> {
+        let Ok(type_) = CControlType::try_from(header.type_) else {
+            return_errno_with_message!(Errno::EINVAL, "the control message type is invalid");
+        };
+
+        match type_ {
+            CControlType::SCM_RIGHTS => {
+                let nr_files = header.payload_len() / size_of::<i32>();
+                if nr_files == 0 {
+                    return Ok(None);
+                }
+                if nr_files > MAX_NR_FILES {
+                    return_errno_with_message!(Errno::EINVAL, "too many files are passed");
+                }
+
+                let file_table = ctx.thread_local.borrow_file_table();
+                let file_table_locked = file_table.unwrap().read();
+
+                let mut files = Vec::with_capacity(nr_files);
+                for _ in 0..nr_files {
+                    let fd = reader.read_val::<i32>()?;
+                    files.push(file_table_locked.get_file(fd)?.clone());
+                }
+
+                Ok(Some(Self::Files(files)))
+            }
+            CControlType::SCM_CREDENTIALS => {
+                if header.payload_len() != size_of::<CUserCred>() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the credentials control message length is invalid"
+                    );
+                }
+
+                let cred = reader.read_val::<CUserCred>()?;
+                cred.check_sendable(ctx)?;
+
+                Ok(Some(Self::Credentials(cred)))
+            }
+        }
+    }
+
+    /// Writes the control message to the writer.
+    ///
+    /// For `SCM_RIGHTS`, the files are installed in the file table of the current process. Files
+    /// that do not fit in the writer are discarded.
+    ///
+    /// Returns whether the control message is truncated.
+    pub(in crate::net) fn write_to(
+        self,
+        writer: &mut VmWriter,
+        flags: SendRecvFlags,
+        ctx: &Context,
+    ) -> Result<bool> {
+        match self {
+            Self::Files(files) => {
+                let max_nr_files =
+                    writer.avail().saturating_sub(size_of::<CControlHeader>()) / size_of::<i32>();
+                if max_nr_files == 0 {
+                    return Ok(true);
+                }
+                let nr_files = files.len().min(max_nr_files);
+
+                let fd_flags = if flags.contains(SendRecvFlags::MSG_CMSG_CLOEXEC) {
+                    FdFlags::CLOEXEC
+                } else {
+                    FdFlags::empty()
+                };
+
+                let fds = {
+                    let file_table = ctx.thread_local.borrow_file_table();
+                    let mut file_table_locked = file_table.unwrap().write();
+                    files
+                        .iter()
+                        .take(nr_files)
+                        .map(|file| file_table_locked.insert(file.clone(), fd_flags))
+                        .flat_map(i32::to_ne_bytes)
+                        .collect::<Vec<_>>()
+                };
+
+                let is_truncated = write_control_message(
+                    writer,
+                    CSocketOptionLevel::SOL_SOCKET,
+                    CControlType::SCM_RIGHTS as i32,
+                    &fds,
+                )?;
+
+                Ok(is_truncated || nr_files < files.len())
+            }
+            Self::Credentials(cred) => write_control_message(
+                writer,
+                CSocketOptionLevel::SOL_SOCKET,
+                CControlType::SCM_CREDENTIALS as i32,
+                cred.as_bytes(),
+            ),
+        }
+    }
+}
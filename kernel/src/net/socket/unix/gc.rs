@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Garbage collection of in-flight UNIX sockets.
+//!
+//! A UNIX socket is _in flight_ if it has been sent with `SCM_RIGHTS` but has not been received
+//! yet. In-flight sockets can form reference cycles that reference counting cannot reclaim. For
+//! example, a socket can be sent to its own receive queue and then closed. The garbage collector
+//! finds such cycles and breaks them by purging the receive queues of unreachable sockets.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/unix/garbage.c>.
+
+use core::mem;
+
+use super::UnixStreamSocket;
+use crate::{fs::file_handle::FileLike, prelude::*};
+
+/// Files that are in flight.
+///
+/// UNIX sockets in the files are tracked while they are in flight, so that unreachable sockets
+/// can be found by [`collect_garbage`].
+pub(super) struct InflightFiles {
+    files: Vec<Arc<dyn FileLike>>,
+}
+
+impl InflightFiles {
+    pub(super) fn new(files: Vec<Arc<dyn FileLike>>) -> Self {
+        let mut inflight_sockets = INFLIGHT_SOCKETS.lock();
+
+        for file in files.iter().filter(|file| is_unix_socket(file)) {
+            inflight_sockets
+                .entry(key_of(file))
+                .or_insert_with(|| InflightSocket {
+                    file: Arc::downgrade(file),
+                    count: 0,
+                })
+                .count += 1;
+        }
+
+        drop(inflight_sockets);
+
+        Self { files }
+    }
+
+    pub(super) fn files(&self) -> &[Arc<dyn FileLike>] {
+        &self.files
+    }
+
+    /// Takes the files out of flight.
+    pub(super) fn into_files(mut self) -> Vec<Arc<dyn FileLike>> {
+        let files = mem::take(&mut self.files);
+        untrack(&files);
+        files
+    }
+}
+
+impl Drop for InflightFiles {
+    fn drop(&mut self) {
+        untrack(&self.files);
+    }
+}
+
+/// Collects in-flight UNIX sockets that are no longer reachable from the user space.
+///
+/// Lock order: This function acquires the lock of in-flight sockets before the states of the
+/// sockets. Therefore, [`InflightFiles`] must not be created or dropped while holding the state
+/// of a UNIX socket.
+pub(super) fn collect_garbage() {
+    let inflight_sockets = INFLIGHT_SOCKETS.lock();
+
+    // Candidates are sockets that are only referenced by in-flight messages.
+    let mut candidates = BTreeMap::new();
+    for (key, inflight) in inflight_sockets.iter() {
+        if inflight.file.strong_count() != inflight.count {
+            continue;
+        }
+        let Some(file) = inflight.file.upgrade() else {
+            continue;
+        };
+        candidates.insert(
+            *key,
+            Candidate {
+                file,
+                nr_inflight: inflight.count,
+                nr_internal: 0,
+                children: Vec::new(),
+            },
+        );
+    }
+
+    // Find candidates in the receive queues of candidates. Such references are internal.
+    let keys = candidates.keys().copied().collect::<Vec<_>>();
+    for key in keys.iter() {
+        let mut children = Vec::new();
+        socket_of(&candidates[key].file).for_each_queued_file(|file| {
+            let child_key = key_of(file);
+            if candidates.contains_key(&child_key) {
+                children.push(child_key);
+            }
+        });
+
+        for child_key in children.iter() {
+            candidates.get_mut(child_key).unwrap().nr_internal += 1;
+        }
+        candidates.get_mut(key).unwrap().children = children;
+    }
+
+    // A candidate with external references is alive, and so is everything reachable from it.
+    let mut alive_keys = keys
+        .iter()
+        .copied()
+        .filter(|key| candidates[key].nr_inflight > candidates[key].nr_internal)
+        .collect::<BTreeSet<_>>();
+    let mut pending_keys = alive_keys.iter().copied().collect::<Vec<_>>();
+    while let Some(key) = pending_keys.pop() {
+        for child_key in candidates[&key].children.iter() {
+            if alive_keys.insert(*child_key) {
+                pending_keys.push(*child_key);
+            }
+        }
+    }
+
+    // The remaining candidates are garbage. Purging their receive queues breaks the cycles.
+    let purged_files = candidates
+        .iter()
+        .filter(|(key, _)| !alive_keys.contains(key))
+        .flat_map(|(_, candidate)| socket_of(&candidate.file).purge_queued_files())
+        .collect::<Vec<_>>();
+
+    drop(inflight_sockets);
+
+    // Dropping the files may release the sockets, which will take the lock again.
+    drop(purged_files);
+    drop(candidates);
+}
+
+static INFLIGHT_SOCKETS: Mutex<BTreeMap<usize, InflightSocket>> = Mutex::new(BTreeMap::new());
+
+struct InflightSocket {
+    file: Weak<dyn FileLike>,
+    count: usize,
+}
+
+struct Candidate {
+    file: Arc<dyn FileLike>,
+    nr_inflight: usize,
+    nr_internal: usize,
+    children: Vec<usize>,
+}
+
+fn untrack(files: &[Arc<dyn FileLike>]) {
+    let mut unix_sockets = files.iter().filter(|file| is_unix_socket(file)).peekable();
+    if unix_sockets.peek().is_none() {
+        return;
+    }
+
+    let mut inflight_sockets = INFLIGHT_SOCKETS.lock();
+
+    for file in unix_sockets {
+        let key = key_of(file);
+        let inflight = inflight_sockets.get_mut(&key).unwrap();
+        inflight.count -= 1;
+        if inflight.count == 0 {
+            inflight_sockets.remove(&key);
+        }
+    }
+}
+
+fn key_of(file: &Arc<dyn FileLike>) -> usize {
+    Arc::as_ptr(file) as *const () as usize
+}
+
+fn is_unix_socket(file: &Arc<dyn FileLike>) -> bool {
+    file.downcast_ref::<UnixStreamSocket>().is_some()
+}
+
+fn socket_of(file: &Arc<dyn FileLike>) -> &UnixStreamSocket {
+    file.downcast_ref::<UnixStreamSocket>().unwrap()
+}
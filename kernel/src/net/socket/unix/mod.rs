@@ -1,8 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod addr;
+mod cred;
+mod ctrl_msg;
+mod gc;
 mod ns;
 mod stream;
 
 pub use addr::UnixSocketAddr;
+pub use cred::CUserCred;
+pub use ctrl_msg::UnixControlMessage;
 pub use stream::UnixStreamSocket;
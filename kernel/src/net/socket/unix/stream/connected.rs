@@ -2,13 +2,16 @@
 
 use core::ops::Deref;
 
-use ostd::sync::PreemptDisabled;
+use ostd::{mm::Infallible, sync::PreemptDisabled};
 
 use crate::{
     events::IoEvents,
-    fs::utils::{Channel, Consumer, Producer},
+    fs::{
+        file_handle::FileLike,
+        utils::{Channel, Consumer, Producer},
+    },
     net::socket::{
-        unix::{addr::UnixSocketAddrBound, UnixSocketAddr},
+        unix::{addr::UnixSocketAddrBound, cred::CUserCred, gc::InflightFiles, UnixSocketAddr},
         SockShutdownCmd,
     },
     prelude::*,
@@ -20,14 +23,21 @@ pub(super) struct Connected {
     addr: AddrView,
     reader: Consumer<u8>,
     writer: Producer<u8>,
+    reader_ancillary: Arc<Mutex<AncillaryQueue>>,
+    writer_ancillary: Arc<Mutex<AncillaryQueue>>,
+    peer_cred: CUserCred,
+    is_seqpacket: bool,
 }
 
 impl Connected {
     pub(super) fn new_pair(
         addr: Option<UnixSocketAddrBound>,
         peer_addr: Option<UnixSocketAddrBound>,
+        cred: CUserCred,
+        peer_cred: CUserCred,
         reader_pollee: Option<Pollee>,
         writer_pollee: Option<Pollee>,
+        is_seqpacket: bool,
     ) -> (Connected, Connected) {
         let (writer_peer, reader_this) =
             Channel::with_capacity_and_pollees(DEFAULT_BUF_SIZE, None, reader_pollee).split();
@@ -36,15 +46,26 @@ impl Connected {
 
         let (addr_this, addr_peer) = AddrView::new_pair(addr, peer_addr);
 
+        let ancillary_this = Arc::new(Mutex::new(AncillaryQueue::new(is_seqpacket)));
+        let ancillary_peer = Arc::new(Mutex::new(AncillaryQueue::new(is_seqpacket)));
+
         let this = Connected {
             addr: addr_this,
             reader: reader_this,
             writer: writer_this,
+            reader_ancillary: ancillary_this.clone(),
+            writer_ancillary: ancillary_peer.clone(),
+            peer_cred,
+            is_seqpacket,
         };
         let peer = Connected {
             addr: addr_peer,
             reader: reader_peer,
             writer: writer_peer,
+            reader_ancillary: ancillary_peer,
+            writer_ancillary: ancillary_this,
+            peer_cred: cred,
+            is_seqpacket,
         };
 
         (this, peer)
@@ -71,12 +92,102 @@ impl Connected {
         Ok(())
     }
 
-    pub(super) fn try_read(&self, writer: &mut dyn MultiWrite) -> Result<usize> {
-        self.reader.try_read(writer)
+    pub(super) fn peer_cred(&self) -> CUserCred {
+        self.peer_cred
     }
 
-    pub(super) fn try_write(&self, reader: &mut dyn MultiRead) -> Result<usize> {
-        self.writer.try_write(reader)
+    pub(super) fn is_seqpacket(&self) -> bool {
+        self.is_seqpacket
+    }
+
+    /// Tries to read bytes and the ancillary data attached to them.
+    ///
+    /// Like Linux, a single read does not cross a change of the credentials if `is_pass_cred` is
+    /// true, and stops after the bytes that carry files. The files are received as soon as any of
+    /// the bytes that carry them are read.
+    ///
+    /// For `SOCK_SEQPACKET` sockets, a single read returns at most one record. If the record does
+    /// not fit in `writer`, the rest of the record is discarded.
+    pub(super) fn try_read(
+        &self,
+        writer: &mut dyn MultiWrite,
+        is_pass_cred: bool,
+    ) -> Result<(usize, Option<Ancillary>)> {
+        let mut ancillary_queue = self.reader_ancillary.lock();
+
+        let limit = ancillary_queue.read_limit(is_pass_cred);
+        let mut limited_writer = LimitedWriter { limit, writer };
+        let read_len = self.reader.try_read(&mut limited_writer)?;
+
+        let mut ancillary = ancillary_queue.consume(read_len);
+
+        if self.is_seqpacket {
+            if let Some(ancillary) = ancillary.as_mut() {
+                let discarded_len = limit - read_len;
+                if discarded_len > 0 {
+                    self.reader.try_read_with(discarded_len, &mut |reader| {
+                        let len = reader.remain();
+                        reader.skip(len);
+                        Ok(len)
+                    })?;
+                    ancillary_queue.consume(discarded_len);
+                }
+                ancillary.discarded_len = discarded_len;
+            }
+        }
+
+        Ok((read_len, ancillary))
+    }
+
+    /// Tries to write bytes and attach the credentials and the files to them.
+    ///
+    /// For `SOCK_SEQPACKET` sockets, the bytes are written as a single record, which is never
+    /// partially written.
+    ///
+    /// The files are taken out of `files` only if some bytes are written, so the same `files` can
+    /// be used again if no bytes can be written at this time.
+    pub(super) fn try_write(
+        &self,
+        reader: &mut dyn MultiRead,
+        cred: CUserCred,
+        files: &mut Option<InflightFiles>,
+    ) -> Result<usize> {
+        let mut ancillary_queue = self.writer_ancillary.lock();
+
+        // TODO: Support zero-length records for `SOCK_SEQPACKET` sockets.
+        let written_len = if self.is_seqpacket {
+            self.writer.try_write_all(reader)?
+        } else {
+            self.writer.try_write(reader)?
+        };
+        if written_len > 0 {
+            ancillary_queue.push(written_len, cred, files.take());
+        }
+
+        Ok(written_len)
+    }
+
+    /// Calls `f` with each file that is in the receive queue.
+    pub(super) fn for_each_queued_file(&self, f: impl FnMut(&Arc<dyn FileLike>)) {
+        let ancillary_queue = self.reader_ancillary.lock();
+
+        ancillary_queue
+            .segments
+            .iter()
+            .filter_map(|segment| segment.files.as_ref())
+            .flat_map(InflightFiles::files)
+            .for_each(f);
+    }
+
+    /// Removes all files from the receive queue.
+    pub(super) fn purge_queued_files(&self) -> Vec<InflightFiles> {
+        let mut ancillary_queue = self.reader_ancillary.lock();
+
+        ancillary_queue
+            .segments
+            .iter_mut()
+            .filter_map(|segment| segment.files.take())
+            .collect()
     }
 
     pub(super) fn shutdown(&self, cmd: SockShutdownCmd) {
@@ -98,6 +209,18 @@ impl Connected {
     }
 }
 
+impl Drop for Connected {
+    fn drop(&mut self) {
+        // Like Linux, the files in the receive queue are released when the socket is released.
+        // Otherwise, they will be kept alive by the peer until the peer is also released.
+        //
+        // Note that the files must be dropped without holding the lock. See
+        // `gc::collect_garbage` for the lock order.
+        let purged_files = self.purge_queued_files();
+        drop(purged_files);
+    }
+}
+
 pub(super) fn combine_io_events(
     mask: IoEvents,
     reader_events: IoEvents,
@@ -156,4 +279,138 @@ impl AddrView {
     }
 }
 
+/// Ancillary data attached to the bytes in a channel.
+///
+/// Bytes written by one write operation form a segment. Adjacent segments with the same
+/// credentials and no files are merged, unless the segments are records of a `SOCK_SEQPACKET`
+/// socket.
+struct AncillaryQueue {
+    segments: VecDeque<Segment>,
+    is_seqpacket: bool,
+}
+
+struct Segment {
+    len: usize,
+    cred: CUserCred,
+    files: Option<InflightFiles>,
+}
+
+/// Ancillary data received with bytes.
+pub(super) struct Ancillary {
+    pub(super) cred: CUserCred,
+    pub(super) files: Option<InflightFiles>,
+    /// The number of bytes discarded because the record is truncated.
+    ///
+    /// This is always zero for `SOCK_STREAM` sockets.
+    pub(super) discarded_len: usize,
+}
+
+impl AncillaryQueue {
+    fn new(is_seqpacket: bool) -> Self {
+        Self {
+            segments: VecDeque::new(),
+            is_seqpacket,
+        }
+    }
+
+    fn push(&mut self, len: usize, cred: CUserCred, files: Option<InflightFiles>) {
+        if files.is_none() && !self.is_seqpacket {
+            if let Some(last) = self.segments.back_mut() {
+                if last.files.is_none() && last.cred == cred {
+                    last.len += len;
+                    return;
+                }
+            }
+        }
+
+        self.segments.push_back(Segment { len, cred, files });
+    }
+
+    /// Returns the maximum number of bytes that can be read at once.
+    fn read_limit(&self, is_pass_cred: bool) -> usize {
+        let Some(first) = self.segments.front() else {
+            return usize::MAX;
+        };
+
+        if self.is_seqpacket {
+            return first.len;
+        }
+
+        let mut limit = 0;
+        for segment in self.segments.iter() {
+            if is_pass_cred && segment.cred != first.cred {
+                break;
+            }
+
+            limit += segment.len;
+
+            if segment.files.is_some() {
+                break;
+            }
+        }
+
+        limit
+    }
+
+    /// Consumes the ancillary data attached to `len` bytes.
+    fn consume(&mut self, mut len: usize) -> Option<Ancillary> {
+        let first = self.segments.front()?;
+        if len == 0 {
+            return None;
+        }
+
+        let mut ancillary = Ancillary {
+            cred: first.cred,
+            files: None,
+            discarded_len: 0,
+        };
+
+        while len > 0 {
+            let Some(segment) = self.segments.front_mut() else {
+                break;
+            };
+
+            if let Some(files) = segment.files.take() {
+                ancillary.files = Some(files);
+            }
+
+            if segment.len > len {
+                segment.len -= len;
+                break;
+            }
+
+            len -= segment.len;
+            self.segments.pop_front();
+        }
+
+        Some(ancillary)
+    }
+}
+
+/// A writer that limits the number of bytes written.
+struct LimitedWriter<'a> {
+    limit: usize,
+    writer: &'a mut dyn MultiWrite,
+}
+
+impl MultiWrite for LimitedWriter<'_> {
+    fn write(&mut self, reader: &mut VmReader<'_, Infallible>) -> Result<usize> {
+        reader.limit(self.limit);
+
+        let written_len = self.writer.write(reader)?;
+        self.limit -= written_len;
+
+        Ok(written_len)
+    }
+
+    fn sum_lens(&self) -> usize {
+        self.writer.sum_lens().min(self.limit)
+    }
+
+    fn skip(&mut self, nbytes: usize) {
+        self.writer.skip(nbytes);
+        self.limit -= nbytes;
+    }
+}
+
 const DEFAULT_BUF_SIZE: usize = 65536;
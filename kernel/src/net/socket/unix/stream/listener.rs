@@ -13,7 +13,10 @@ use crate::{
     events::IoEvents,
     fs::file_handle::FileLike,
     net::socket::{
-        unix::addr::{UnixSocketAddrBound, UnixSocketAddrKey},
+        unix::{
+            addr::{UnixSocketAddrBound, UnixSocketAddrKey},
+            cred::CUserCred,
+        },
         SockShutdownCmd, SocketAddr,
     },
     prelude::*,
@@ -34,9 +37,17 @@ impl Listener {
         backlog: usize,
         is_read_shutdown: bool,
         is_write_shutdown: bool,
+        is_seqpacket: bool,
     ) -> Self {
         let backlog = BACKLOG_TABLE
-            .add_backlog(addr, reader_pollee, backlog, is_read_shutdown)
+            .add_backlog(
+                addr,
+                CUserCred::current_effective(),
+                reader_pollee,
+                backlog,
+                is_read_shutdown,
+                is_seqpacket,
+            )
             .unwrap();
         writer_pollee.invalidate();
 
@@ -51,11 +62,15 @@ impl Listener {
         self.backlog.addr()
     }
 
-    pub(super) fn try_accept(&self) -> Result<(Arc<dyn FileLike>, SocketAddr)> {
+    pub(super) fn cred(&self) -> CUserCred {
+        self.backlog.cred
+    }
+
+    pub(super) fn try_accept(&self, is_pass_cred: bool) -> Result<(Arc<dyn FileLike>, SocketAddr)> {
         let connected = self.backlog.pop_incoming()?;
         let peer_addr = connected.peer_addr().into();
 
-        let socket = UnixStreamSocket::new_connected(connected, false);
+        let socket = UnixStreamSocket::new_connected(connected, false, is_pass_cred);
         Ok((socket, peer_addr))
     }
 
@@ -119,9 +134,11 @@ impl BacklogTable {
     fn add_backlog(
         &self,
         addr: UnixSocketAddrBound,
+        cred: CUserCred,
         pollee: Pollee,
         backlog: usize,
         is_shutdown: bool,
+        is_seqpacket: bool,
     ) -> Option<Arc<Backlog>> {
         let addr_key = addr.to_key();
 
@@ -133,7 +150,14 @@ impl BacklogTable {
 
         // Note that the cached events can be correctly inherited from `Init`, so there is no need
         // to explicitly call `Pollee::invalidate`.
-        let new_backlog = Arc::new(Backlog::new(addr, pollee, backlog, is_shutdown));
+        let new_backlog = Arc::new(Backlog::new(
+            addr,
+            cred,
+            pollee,
+            backlog,
+            is_shutdown,
+            is_seqpacket,
+        ));
         backlog_sockets.insert(addr_key, new_backlog.clone());
 
         Some(new_backlog)
@@ -150,14 +174,23 @@ impl BacklogTable {
 
 pub(super) struct Backlog {
     addr: UnixSocketAddrBound,
+    cred: CUserCred,
     pollee: Pollee,
     backlog: AtomicUsize,
     incoming_conns: SpinLock<Option<VecDeque<Connected>>>,
     wait_queue: WaitQueue,
+    is_seqpacket: bool,
 }
 
 impl Backlog {
-    fn new(addr: UnixSocketAddrBound, pollee: Pollee, backlog: usize, is_shutdown: bool) -> Self {
+    fn new(
+        addr: UnixSocketAddrBound,
+        cred: CUserCred,
+        pollee: Pollee,
+        backlog: usize,
+        is_shutdown: bool,
+        is_seqpacket: bool,
+    ) -> Self {
         let incoming_sockets = if is_shutdown {
             None
         } else {
@@ -166,10 +199,12 @@ impl Backlog {
 
         Self {
             addr,
+            cred,
             pollee,
             backlog: AtomicUsize::new(backlog),
             incoming_conns: SpinLock::new(incoming_sockets),
             wait_queue: WaitQueue::new(),
+            is_seqpacket,
         }
     }
 
@@ -205,10 +240,13 @@ impl Backlog {
     fn shutdown(&self) {
         let mut incoming_conns = self.incoming_conns.lock();
 
-        *incoming_conns = None;
+        // The connections must be dropped after releasing the spin lock, since dropping them may
+        // release in-flight files, which requires sleeping.
+        let dropped_conns = incoming_conns.take();
         self.pollee.notify(IoEvents::HUP);
 
         drop(incoming_conns);
+        drop(dropped_conns);
 
         self.wait_queue.wake_all();
     }
@@ -234,10 +272,28 @@ impl Backlog {
 }
 
 impl Backlog {
+    /// Pushes an incoming connection to the backlog.
+    ///
+    /// `cred` is the credentials of the connecting socket, which will be seen by the accepted
+    /// socket via `SO_PEERCRED`.
+    ///
+    /// Like Linux, the connecting socket must be of the same type as the listening socket.
     pub(super) fn push_incoming(
         &self,
         init: Init,
+        cred: CUserCred,
+        is_seqpacket: bool,
     ) -> core::result::Result<Connected, (Error, Init)> {
+        if is_seqpacket != self.is_seqpacket {
+            return Err((
+                Error::with_message(
+                    Errno::EPROTOTYPE,
+                    "the listening socket is of a different type",
+                ),
+                init,
+            ));
+        }
+
         let mut locked_incoming_conns = self.incoming_conns.lock();
 
         let Some(incoming_conns) = &mut *locked_incoming_conns else {
@@ -260,7 +316,8 @@ impl Backlog {
             ));
         }
 
-        let (client_conn, server_conn) = init.into_connected(self.addr.clone());
+        let (client_conn, server_conn) =
+            init.into_connected(self.addr.clone(), cred, self.cred, self.is_seqpacket);
 
         incoming_conns.push_back(server_conn);
         self.pollee.notify(IoEvents::IN);
@@ -5,17 +5,25 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use takeable::Takeable;
 
 use super::{
-    connected::Connected,
+    connected::{Ancillary, Connected},
     init::Init,
     listener::{get_backlog, Backlog, Listener},
 };
 use crate::{
     events::IoEvents,
     fs::file_handle::FileLike,
+    match_sock_option_mut, match_sock_option_ref,
     net::socket::{
+        options::{PassCred, PeerCred, SocketOption},
         private::SocketPrivate,
-        unix::UnixSocketAddr,
-        util::{send_recv_flags::SendRecvFlags, socket_addr::SocketAddr, MessageHeader},
+        unix::{
+            cred::CUserCred,
+            gc::{self, InflightFiles},
+            UnixControlMessage, UnixSocketAddr,
+        },
+        util::{
+            send_recv_flags::SendRecvFlags, socket_addr::SocketAddr, ControlMessage, MessageHeader,
+        },
         SockShutdownCmd, Socket,
     },
     prelude::*,
@@ -23,23 +31,39 @@ use crate::{
     util::{MultiRead, MultiWrite},
 };
 
+/// A UNIX socket of the `SOCK_STREAM` or `SOCK_SEQPACKET` type.
+///
+/// `SOCK_SEQPACKET` sockets are connection-oriented like `SOCK_STREAM` sockets, but they preserve
+/// the boundaries of the records written by each send operation.
 pub struct UnixStreamSocket {
     state: RwMutex<Takeable<State>>,
     is_nonblocking: AtomicBool,
+    is_pass_cred: AtomicBool,
+    is_seqpacket: bool,
 }
 
 impl UnixStreamSocket {
-    pub(super) fn new_init(init: Init, is_nonblocking: bool) -> Arc<Self> {
+    pub(super) fn new_init(init: Init, is_nonblocking: bool, is_seqpacket: bool) -> Arc<Self> {
         Arc::new(Self {
             state: RwMutex::new(Takeable::new(State::Init(init))),
             is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_pass_cred: AtomicBool::new(false),
+            is_seqpacket,
         })
     }
 
-    pub(super) fn new_connected(connected: Connected, is_nonblocking: bool) -> Arc<Self> {
+    pub(super) fn new_connected(
+        connected: Connected,
+        is_nonblocking: bool,
+        is_pass_cred: bool,
+    ) -> Arc<Self> {
+        let is_seqpacket = connected.is_seqpacket();
+
         Arc::new(Self {
             state: RwMutex::new(Takeable::new(State::Connected(connected))),
             is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_pass_cred: AtomicBool::new(is_pass_cred),
+            is_seqpacket,
         })
     }
 }
@@ -51,30 +75,47 @@ enum State {
 }
 
 impl UnixStreamSocket {
-    pub fn new(is_nonblocking: bool) -> Arc<Self> {
-        Self::new_init(Init::new(), is_nonblocking)
+    pub fn new(is_nonblocking: bool, is_seqpacket: bool) -> Arc<Self> {
+        Self::new_init(Init::new(), is_nonblocking, is_seqpacket)
     }
 
-    pub fn new_pair(is_nonblocking: bool) -> (Arc<Self>, Arc<Self>) {
-        let (conn_a, conn_b) = Connected::new_pair(None, None, None, None);
+    pub fn new_pair(is_nonblocking: bool, is_seqpacket: bool) -> (Arc<Self>, Arc<Self>) {
+        let cred = CUserCred::current_effective();
+        let (conn_a, conn_b) =
+            Connected::new_pair(None, None, cred, cred, None, None, is_seqpacket);
         (
-            Self::new_connected(conn_a, is_nonblocking),
-            Self::new_connected(conn_b, is_nonblocking),
+            Self::new_connected(conn_a, is_nonblocking, false),
+            Self::new_connected(conn_b, is_nonblocking, false),
         )
     }
 
-    fn try_send(&self, buf: &mut dyn MultiRead, _flags: SendRecvFlags) -> Result<usize> {
+    fn is_pass_cred(&self) -> bool {
+        self.is_pass_cred.load(Ordering::Relaxed)
+    }
+
+    fn try_send(
+        &self,
+        buf: &mut dyn MultiRead,
+        cred: CUserCred,
+        files: &mut Option<InflightFiles>,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
         match self.state.read().as_ref() {
-            State::Connected(connected) => connected.try_write(buf),
+            State::Connected(connected) => connected.try_write(buf, cred, files),
             State::Init(_) | State::Listen(_) => {
                 return_errno_with_message!(Errno::ENOTCONN, "the socket is not connected")
             }
         }
     }
 
-    fn try_recv(&self, buf: &mut dyn MultiWrite, _flags: SendRecvFlags) -> Result<usize> {
+    fn try_recv(
+        &self,
+        buf: &mut dyn MultiWrite,
+        is_pass_cred: bool,
+        _flags: SendRecvFlags,
+    ) -> Result<(usize, Option<Ancillary>)> {
         match self.state.read().as_ref() {
-            State::Connected(connected) => connected.try_read(buf),
+            State::Connected(connected) => connected.try_read(buf, is_pass_cred),
             State::Init(_) | State::Listen(_) => {
                 return_errno_with_message!(Errno::EINVAL, "the socket is not connected")
             }
@@ -82,10 +123,12 @@ impl UnixStreamSocket {
     }
 
     fn try_connect(&self, backlog: &Arc<Backlog>) -> Result<()> {
+        let cred = CUserCred::current_effective();
+
         let mut state = self.state.write();
 
         state.borrow_result(|owned_state| {
-            let init = match owned_state {
+            let mut init = match owned_state {
                 State::Init(init) => init,
                 State::Listen(listener) => {
                     return (
@@ -107,7 +150,16 @@ impl UnixStreamSocket {
                 }
             };
 
-            let connected = match backlog.push_incoming(init) {
+            // Like Linux, an unbound socket is autobound before connecting if `SO_PASSCRED` is set.
+            //
+            // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/unix/af_unix.c>.
+            if init.addr().is_none() && self.is_pass_cred() {
+                if let Err(err) = init.bind(UnixSocketAddr::Unnamed) {
+                    return (State::Init(init), Err(err));
+                }
+            }
+
+            let connected = match backlog.push_incoming(init, cred, self.is_seqpacket) {
                 Ok(connected) => connected,
                 Err((err, init)) => return (State::Init(init), Err(err)),
             };
@@ -118,7 +170,7 @@ impl UnixStreamSocket {
 
     fn try_accept(&self) -> Result<(Arc<dyn FileLike>, SocketAddr)> {
         match self.state.read().as_ref() {
-            State::Listen(listen) => listen.try_accept() as _,
+            State::Listen(listen) => listen.try_accept(self.is_pass_cred()) as _,
             State::Init(_) | State::Connected(_) => {
                 return_errno_with_message!(Errno::EINVAL, "the socket is not listening")
             }
@@ -126,6 +178,30 @@ impl UnixStreamSocket {
     }
 }
 
+impl UnixStreamSocket {
+    /// Calls `f` with each file that is in the receive queue.
+    ///
+    /// This is used by the garbage collector of in-flight sockets.
+    pub(in crate::net::socket::unix) fn for_each_queued_file(
+        &self,
+        f: impl FnMut(&Arc<dyn FileLike>),
+    ) {
+        if let State::Connected(connected) = self.state.read().as_ref() {
+            connected.for_each_queued_file(f);
+        }
+    }
+
+    /// Removes all files from the receive queue.
+    ///
+    /// This is used by the garbage collector of in-flight sockets.
+    pub(in crate::net::socket::unix) fn purge_queued_files(&self) -> Vec<InflightFiles> {
+        match self.state.read().as_ref() {
+            State::Connected(connected) => connected.purge_queued_files(),
+            State::Init(_) | State::Listen(_) => Vec::new(),
+        }
+    }
+}
+
 impl Pollable for UnixStreamSocket {
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
         let inner = self.state.read();
@@ -200,7 +276,7 @@ impl Socket for UnixStreamSocket {
                 }
             };
 
-            let listener = match init.listen(backlog) {
+            let listener = match init.listen(backlog, self.is_seqpacket) {
                 Ok(listener) => listener,
                 Err((err, init)) => {
                     return (State::Init(init), Err(err));
@@ -258,15 +334,35 @@ impl Socket for UnixStreamSocket {
         }
 
         let MessageHeader {
-            control_message, ..
+            control_messages, ..
         } = message_header;
 
-        if control_message.is_some() {
-            // TODO: Support sending control message
-            warn!("sending control message is not supported");
+        let mut cred = None;
+        let mut files = Vec::new();
+        for control_message in control_messages {
+            let ControlMessage::Unix(message) = control_message else {
+                // TODO: Support timestamping on UNIX sockets
+                continue;
+            };
+            match message {
+                UnixControlMessage::Files(mut more_files) => files.append(&mut more_files),
+                UnixControlMessage::Credentials(explicit_cred) => cred = Some(explicit_cred),
+            }
         }
 
-        self.block_on(IoEvents::OUT, || self.try_send(reader, flags))
+        let cred = cred.unwrap_or_else(CUserCred::current_real);
+        let mut files = if files.is_empty() {
+            None
+        } else {
+            // The files may close a reference cycle of in-flight sockets. Collect the unreachable
+            // sockets before sending more.
+            gc::collect_garbage();
+            Some(InflightFiles::new(files))
+        };
+
+        self.block_on_msg(flags, IoEvents::OUT, || {
+            self.try_send(reader, cred, &mut files, flags)
+        })
     }
 
     fn recvmsg(
@@ -279,12 +375,71 @@ impl Socket for UnixStreamSocket {
             warn!("unsupported flags: {:?}", flags);
         }
 
-        let received_bytes = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+        let is_pass_cred = self.is_pass_cred();
+        let (mut received_bytes, ancillary) = self.block_on_msg(flags, IoEvents::IN, || {
+            self.try_recv(writer, is_pass_cred, flags)
+        })?;
+
+        let mut control_messages = Vec::new();
+        let mut is_truncated = false;
+        if let Some(Ancillary {
+            cred,
+            files,
+            discarded_len,
+        }) = ancillary
+        {
+            if is_pass_cred {
+                control_messages.push(ControlMessage::Unix(UnixControlMessage::Credentials(cred)));
+            }
+            if let Some(files) = files {
+                control_messages.push(ControlMessage::Unix(UnixControlMessage::Files(
+                    files.into_files(),
+                )));
+            }
 
-        // TODO: Receive control message
+            // Like Linux, the real length of the record is returned if `MSG_TRUNC` is specified.
+            is_truncated = discarded_len > 0;
+            if flags.contains(SendRecvFlags::MSG_TRUNC) {
+                received_bytes += discarded_len;
+            }
+        }
 
-        let message_header = MessageHeader::new(None, None);
+        let mut message_header = MessageHeader::new(None, control_messages);
+        if is_truncated {
+            message_header.set_truncated();
+        }
 
         Ok((received_bytes, message_header))
     }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            pass_cred: PassCred => {
+                pass_cred.set(self.is_pass_cred());
+            },
+            peer_cred: PeerCred => {
+                let cred = match self.state.read().as_ref() {
+                    State::Connected(connected) => connected.peer_cred(),
+                    // Like Linux, a listening socket reports its own credentials.
+                    State::Listen(listener) => listener.cred(),
+                    State::Init(_) => CUserCred::unknown(),
+                };
+                peer_cred.set(cred);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            pass_cred: PassCred => {
+                self.is_pass_cred.store(*pass_cred.get().unwrap(), Ordering::Relaxed);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to set is unknown")
+        });
+
+        Ok(())
+    }
 }
@@ -0,0 +1,344 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The error queue of sockets.
+//!
+//! Besides ICMP errors (requested with `IP_RECVERR` or `IPV6_RECVERR`), the error queue reports
+//! the completions of zero-copy transmissions (requested with `MSG_ZEROCOPY`) and the transmit
+//! timestamps (requested with `SO_TIMESTAMPING`). The messages in the error queue are received
+//! with `MSG_ERRQUEUE`.
+//!
+//! Reference: <https://docs.kernel.org/networking/msg_zerocopy.html>.
+
+use core::time::Duration;
+
+use aster_bigtcp::{socket::IcmpError, time::PacketClock};
+
+use super::{
+    options::SocketOptionSet,
+    socket_addr::SocketAddr,
+    timestamp::{timestamp_control_messages, RealTimePacketClock, TimestampingFlags},
+    write_control_message, MessageHeader,
+};
+use crate::{
+    net::socket::ControlMessage,
+    prelude::*,
+    util::{
+        net::{socket_addr_to_c_bytes, CSocketOptionLevel},
+        MultiWrite,
+    },
+};
+
+/// The error queue of a socket.
+#[derive(Default)]
+pub struct ErrorQueue {
+    entries: VecDeque<ErrorQueueEntry>,
+    /// The ID of the next zero-copy transmission.
+    next_zerocopy_id: u32,
+    /// The key of the transmit timestamps of the next message (if `SOF_TIMESTAMPING_OPT_ID` is
+    /// set).
+    next_timestamp_key: u32,
+    /// The pending socket error (i.e., `SO_ERROR`).
+    ///
+    /// Like Linux, the error is reported by the next socket operation, and it is reset to the
+    /// error of the next ICMP error message when an ICMP error message is received from the
+    /// error queue.
+    socket_error: Option<Errno>,
+}
+
+#[derive(Debug)]
+enum ErrorQueueEntry {
+    /// The zero-copy transmissions whose IDs range from `lo` to `hi` (inclusive) complete.
+    ZeroCopy { lo: u32, hi: u32 },
+    /// A message is timestamped when it is sent.
+    Timestamp {
+        key: u32,
+        type_: TimestampType,
+        time: Duration,
+    },
+    /// A sent packet cannot be delivered, as reported by an ICMP error message.
+    Icmp {
+        errno: Errno,
+        error: IcmpError,
+        /// The address of the node that generates the ICMP error message.
+        offender: SocketAddr,
+        /// The destination of the packet.
+        remote: SocketAddr,
+    },
+}
+
+/// The types of transmit timestamps (i.e., `SCM_TSTAMP_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L59>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampType {
+    /// The message is handed to the device.
+    Snd = 0,
+    /// The message enters the packet scheduler.
+    Sched = 1,
+}
+
+/// The maximum number of entries in the error queue.
+///
+/// Linux limits the error queue by the size of the receive buffer. New entries are dropped if
+/// the limit is reached.
+const MAX_NR_ENTRIES: usize = 1024;
+
+impl ErrorQueue {
+    /// Returns whether the error queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the key of the transmit timestamps of the next message.
+    ///
+    /// The key is advanced only after the message is sent. See [`Self::on_message_sent`].
+    pub fn timestamp_key(&self, flags: TimestampingFlags) -> u32 {
+        if flags.contains(TimestampingFlags::OPT_ID) {
+            self.next_timestamp_key
+        } else {
+            0
+        }
+    }
+
+    /// Resets the key of the transmit timestamps.
+    ///
+    /// This should be called when `SOF_TIMESTAMPING_OPT_ID` is enabled.
+    pub fn reset_timestamp_key(&mut self) {
+        self.next_timestamp_key = 0;
+    }
+
+    /// Records that a message is sent.
+    ///
+    /// The message is timestamped according to `flags`, and its transmission completes
+    /// immediately if it is sent with `MSG_ZEROCOPY`, since the data have already been copied.
+    ///
+    /// Returns whether new entries may be pushed to the error queue.
+    pub fn on_message_sent(&mut self, flags: TimestampingFlags, is_zerocopy: bool) -> bool {
+        let is_sched = flags.contains(TimestampingFlags::TX_SCHED);
+        if is_sched {
+            let key = self.timestamp_key(flags);
+            self.push_timestamp(key, TimestampType::Sched, RealTimePacketClock::now());
+        }
+        if flags.intersects(TimestampingFlags::TX_RECORD_MASK)
+            && flags.contains(TimestampingFlags::OPT_ID)
+        {
+            self.next_timestamp_key = self.next_timestamp_key.wrapping_add(1);
+        }
+
+        if is_zerocopy {
+            let id = self.next_zerocopy_id;
+            self.next_zerocopy_id = id.wrapping_add(1);
+            self.push_zerocopy(id);
+        }
+
+        is_sched || is_zerocopy
+    }
+
+    fn push_zerocopy(&mut self, id: u32) {
+        // Like Linux, adjacent completions are merged into one entry.
+        if let Some(ErrorQueueEntry::ZeroCopy { hi, .. }) = self.entries.back_mut() {
+            if hi.wrapping_add(1) == id {
+                *hi = id;
+                return;
+            }
+        }
+
+        self.push_entry(ErrorQueueEntry::ZeroCopy { lo: id, hi: id });
+    }
+
+    /// Pushes a transmit timestamp of a message.
+    pub fn push_timestamp(&mut self, key: u32, type_: TimestampType, time: Duration) {
+        self.push_entry(ErrorQueueEntry::Timestamp { key, type_, time });
+    }
+
+    /// Pushes an ICMP error message that reports an undeliverable packet.
+    ///
+    /// The socket error is also set to `errno`.
+    pub fn push_icmp_error(
+        &mut self,
+        errno: Errno,
+        error: IcmpError,
+        offender: SocketAddr,
+        remote: SocketAddr,
+    ) {
+        self.push_entry(ErrorQueueEntry::Icmp {
+            errno,
+            error,
+            offender,
+            remote,
+        });
+        self.socket_error = Some(errno);
+    }
+
+    /// Sets the socket error.
+    pub fn set_socket_error(&mut self, errno: Errno) {
+        self.socket_error = Some(errno);
+    }
+
+    /// Returns whether there is a pending socket error.
+    pub fn has_socket_error(&self) -> bool {
+        self.socket_error.is_some()
+    }
+
+    /// Takes the pending socket error.
+    pub fn take_socket_error(&mut self) -> Option<Error> {
+        self.socket_error
+            .take()
+            .map(|errno| Error::with_message(errno, "an ICMP error message is received"))
+    }
+
+    fn push_entry(&mut self, entry: ErrorQueueEntry) {
+        if self.entries.len() < MAX_NR_ENTRIES {
+            self.entries.push_back(entry);
+        }
+    }
+
+    /// Receives a message from the error queue.
+    ///
+    /// Only the messages that report ICMP errors carry data (i.e., the payload of the
+    /// undeliverable packet). Unlike Linux, the sent messages are not looped back along with their
+    /// transmit timestamps, as if `SOF_TIMESTAMPING_OPT_TSONLY` is always set.
+    pub fn recv(
+        &mut self,
+        writer: &mut dyn MultiWrite,
+        options: &SocketOptionSet,
+        is_ipv6: bool,
+    ) -> Result<(usize, MessageHeader)> {
+        let Some(entry) = self.entries.pop_front() else {
+            return_errno_with_message!(Errno::EAGAIN, "the error queue is empty");
+        };
+
+        let mut control_messages = Vec::new();
+        let mut recv_bytes = 0;
+        let mut addr = None;
+        let mut offender_addr = None;
+        let error = match entry {
+            ErrorQueueEntry::ZeroCopy { lo, hi } => CSockExtendedErr {
+                errno: 0,
+                origin: SO_EE_ORIGIN_ZEROCOPY,
+                type_: 0,
+                code: SO_EE_CODE_ZEROCOPY_COPIED,
+                pad: 0,
+                info: lo,
+                data: hi,
+            },
+            ErrorQueueEntry::Timestamp { key, type_, time } => {
+                control_messages = timestamp_control_messages(options, time, true);
+                CSockExtendedErr {
+                    errno: Errno::ENOMSG as u32,
+                    origin: SO_EE_ORIGIN_TIMESTAMPING,
+                    type_: 0,
+                    code: 0,
+                    pad: 0,
+                    info: type_ as u32,
+                    data: key,
+                }
+            }
+            ErrorQueueEntry::Icmp {
+                errno,
+                error,
+                offender,
+                remote,
+            } => {
+                self.update_socket_error();
+
+                recv_bytes = writer.write(&mut VmReader::from(error.payload.as_slice()))?;
+                addr = Some(remote);
+                offender_addr = Some(offender);
+                CSockExtendedErr {
+                    errno: errno as u32,
+                    origin: if error.is_ipv6 {
+                        SO_EE_ORIGIN_ICMP6
+                    } else {
+                        SO_EE_ORIGIN_ICMP
+                    },
+                    type_: error.type_,
+                    code: error.code,
+                    pad: 0,
+                    info: error.mtu,
+                    data: 0,
+                }
+            }
+        };
+        control_messages.push(ControlMessage::Error(ErrorControlMessage {
+            error,
+            offender: offender_addr,
+            is_ipv6,
+        }));
+
+        Ok((recv_bytes, MessageHeader::new(addr, control_messages)))
+    }
+
+    /// Resets the socket error after an ICMP error message is received from the error queue.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/core/skbuff.c#L5488>.
+    fn update_socket_error(&mut self) {
+        self.socket_error = self.entries.iter().find_map(|entry| match entry {
+            ErrorQueueEntry::Icmp { errno, .. } => Some(*errno),
+            _ => None,
+        });
+    }
+}
+
+/// A control message that carries an extended error (i.e., `IP_RECVERR` or `IPV6_RECVERR`).
+#[derive(Debug)]
+pub struct ErrorControlMessage {
+    error: CSockExtendedErr,
+    offender: Option<SocketAddr>,
+    is_ipv6: bool,
+}
+
+/// The extended error (i.e., `struct sock_extended_err`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L9>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CSockExtendedErr {
+    errno: u32,
+    origin: u8,
+    type_: u8,
+    code: u8,
+    pad: u8,
+    info: u32,
+    data: u32,
+}
+
+const SO_EE_ORIGIN_ICMP: u8 = 2;
+const SO_EE_ORIGIN_ICMP6: u8 = 3;
+const SO_EE_ORIGIN_TIMESTAMPING: u8 = 4;
+const SO_EE_ORIGIN_ZEROCOPY: u8 = 5;
+
+const SO_EE_CODE_ZEROCOPY_COPIED: u8 = 1;
+
+/// The type of control messages at the IP level that carry extended errors.
+const IP_RECVERR: i32 = 11;
+/// The type of control messages at the IPv6 level that carry extended errors.
+const IPV6_RECVERR: i32 = 25;
+
+/// The sizes of `struct sockaddr_in` and `struct sockaddr_in6`.
+const SOCKADDR_IN_LEN: usize = 16;
+const SOCKADDR_IN6_LEN: usize = 28;
+
+impl ErrorControlMessage {
+    /// Writes the control message to the writer.
+    ///
+    /// Returns whether the control message is truncated.
+    pub(in crate::net) fn write_to(self, writer: &mut VmWriter) -> Result<bool> {
+        // The extended error is followed by the address of the node that causes the error. If
+        // the error is caused locally, the address is unspecified.
+        let (level, type_, addr_len) = if self.is_ipv6 {
+            (CSocketOptionLevel::SOL_IPV6, IPV6_RECVERR, SOCKADDR_IN6_LEN)
+        } else {
+            (CSocketOptionLevel::SOL_IP, IP_RECVERR, SOCKADDR_IN_LEN)
+        };
+
+        let mut payload = self.error.as_bytes().to_vec();
+        match self.offender {
+            Some(offender) => payload.extend(socket_addr_to_c_bytes(&offender)),
+            None => payload.resize(payload.len() + addr_len, 0),
+        }
+
+        write_control_message(writer, level, type_, &payload)
+    }
+}
@@ -0,0 +1,496 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Classic BPF socket filters.
+//!
+//! A socket filter is a classic BPF program attached to a socket with `SO_ATTACH_FILTER`. The
+//! program runs on each packet that the socket is about to receive. Its return value is the
+//! number of bytes of the packet to keep, where zero means that the packet is dropped.
+//!
+//! Reference: <https://www.kernel.org/doc/html/v6.13/networking/filter.html>.
+
+use crate::prelude::*;
+
+/// An instruction of a classic BPF program.
+///
+/// This is `struct sock_filter` in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/filter.h#L24>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CSockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+/// The number of words in the scratch memory (i.e., `BPF_MEMWORDS`).
+const NR_MEM_WORDS: usize = 16;
+
+// Special offsets for loading data that is not in the packet.
+const SKF_AD_OFF: i32 = -0x1000;
+const SKF_NET_OFF: i32 = -0x100000;
+const SKF_LL_OFF: i32 = -0x200000;
+
+/// A validated classic BPF program that can be attached to sockets.
+#[derive(Debug, Clone)]
+pub struct SocketFilter {
+    insns: Arc<[Insn]>,
+}
+
+/// The packet that a socket filter runs on.
+#[derive(Debug)]
+pub(in crate::net) struct FilterInput<'a> {
+    /// The whole frame, which starts with the link-layer header.
+    pub(in crate::net) frame: &'a [u8],
+    /// The offset of the data that the socket sees.
+    ///
+    /// Offsets in the program are relative to this offset, unless `SKF_NET_OFF` or `SKF_LL_OFF`
+    /// is used.
+    pub(in crate::net) data_offset: usize,
+    /// The offset of the network-layer header.
+    pub(in crate::net) net_offset: usize,
+    /// The link-layer protocol (e.g., `ETH_P_IP`).
+    pub(in crate::net) protocol: u16,
+    /// The packet type (e.g., `PACKET_HOST`).
+    pub(in crate::net) pkttype: u8,
+    /// The index of the interface that the packet goes through.
+    pub(in crate::net) ifindex: u32,
+    /// The hardware type of the interface (e.g., `ARPHRD_ETHER`).
+    pub(in crate::net) hatype: u16,
+}
+
+impl SocketFilter {
+    /// The maximum number of instructions in a program (i.e., `BPF_MAXINSNS`).
+    pub const MAX_NR_INSNS: usize = 4096;
+
+    /// Creates a socket filter after validating the instructions.
+    ///
+    /// Like Linux, this method fails with [`Errno::EINVAL`] if the program contains unknown
+    /// instructions, jumps out of bounds, or does not end with a return instruction.
+    pub fn new(c_insns: &[CSockFilter]) -> Result<Self> {
+        if c_insns.is_empty() || c_insns.len() > Self::MAX_NR_INSNS {
+            return_errno_with_message!(Errno::EINVAL, "the number of instructions is invalid");
+        }
+
+        let insns = c_insns
+            .iter()
+            .enumerate()
+            .map(|(pc, c_insn)| Insn::decode(c_insn, c_insns.len() - pc - 1))
+            .collect::<Option<Arc<[_]>>>()
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the instruction is invalid"))?;
+
+        if !matches!(insns.last(), Some(Insn::Ret(_))) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the program does not end with a return instruction"
+            );
+        }
+
+        Ok(Self { insns })
+    }
+
+    /// Runs the filter on the packet.
+    ///
+    /// This method returns the number of bytes of the packet to keep.
+    pub(in crate::net) fn run(&self, input: &FilterInput) -> u32 {
+        let mut acc: u32 = 0;
+        let mut idx: u32 = 0;
+        let mut mem = [0u32; NR_MEM_WORDS];
+
+        let mut pc = 0;
+        loop {
+            // This will not go out of bounds, since all jumps have been validated and the
+            // program always ends with a return instruction.
+            let insn = self.insns[pc];
+            pc += 1;
+
+            match insn {
+                Insn::LdImm(k) => acc = k,
+                Insn::LdAbs(size, k) => match input.load(size, k as i32) {
+                    Some(val) => acc = val,
+                    None => return 0,
+                },
+                Insn::LdInd(size, k) => match input.load(size, idx.wrapping_add(k) as i32) {
+                    Some(val) => acc = val,
+                    None => return 0,
+                },
+                Insn::LdAncillary(ancillary) => acc = input.load_ancillary(ancillary),
+                Insn::LdMem(i) => acc = mem[i],
+                Insn::LdLen => acc = input.data_len() as u32,
+                Insn::LdxImm(k) => idx = k,
+                Insn::LdxMem(i) => idx = mem[i],
+                Insn::LdxLen => idx = input.data_len() as u32,
+                Insn::LdxMsh(k) => match input.load(Size::Byte, k as i32) {
+                    Some(val) => idx = (val & 0xf) << 2,
+                    None => return 0,
+                },
+                Insn::St(i) => mem[i] = acc,
+                Insn::Stx(i) => mem[i] = idx,
+                Insn::Alu(op, src) => {
+                    let operand = match src {
+                        Src::K(k) => k,
+                        Src::X => idx,
+                    };
+                    acc = match op.apply(acc, operand) {
+                        Some(val) => val,
+                        None => return 0,
+                    };
+                }
+                Insn::Neg => acc = acc.wrapping_neg(),
+                Insn::Ja(k) => pc += k as usize,
+                Insn::Jmp(op, src, jt, jf) => {
+                    let operand = match src {
+                        Src::K(k) => k,
+                        Src::X => idx,
+                    };
+                    let is_true = match op {
+                        JmpOp::Jeq => acc == operand,
+                        JmpOp::Jgt => acc > operand,
+                        JmpOp::Jge => acc >= operand,
+                        JmpOp::Jset => acc & operand != 0,
+                    };
+                    pc += if is_true { jt } else { jf } as usize;
+                }
+                Insn::Ret(ret) => {
+                    return match ret {
+                        RetVal::K(k) => k,
+                        RetVal::A => acc,
+                        RetVal::X => idx,
+                    }
+                }
+                Insn::Tax => idx = acc,
+                Insn::Txa => acc = idx,
+            }
+        }
+    }
+}
+
+impl FilterInput<'_> {
+    fn data_len(&self) -> usize {
+        self.frame.len() - self.data_offset
+    }
+
+    /// Loads a big-endian value at the offset.
+    ///
+    /// This method returns `None` if the offset is out of bounds.
+    fn load(&self, size: Size, offset: i32) -> Option<u32> {
+        let start = if offset >= 0 {
+            self.data_offset.checked_add(offset as usize)?
+        } else if offset >= SKF_AD_OFF {
+            return None;
+        } else if offset >= SKF_NET_OFF {
+            self.net_offset + (offset - SKF_NET_OFF) as usize
+        } else if offset >= SKF_LL_OFF {
+            (offset - SKF_LL_OFF) as usize
+        } else {
+            return None;
+        };
+
+        let bytes = self.frame.get(start..start.checked_add(size.len())?)?;
+        Some(bytes.iter().fold(0, |val, byte| (val << 8) | *byte as u32))
+    }
+
+    fn load_ancillary(&self, ancillary: Ancillary) -> u32 {
+        match ancillary {
+            Ancillary::Protocol => self.protocol as u32,
+            Ancillary::PktType => self.pkttype as u32,
+            Ancillary::IfIndex => self.ifindex,
+            Ancillary::HaType => self.hatype as u32,
+            // These are not supported and are always zero.
+            Ancillary::Mark
+            | Ancillary::Queue
+            | Ancillary::RxHash
+            | Ancillary::VlanTag
+            | Ancillary::VlanTagPresent => 0,
+        }
+    }
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy)]
+enum Insn {
+    LdImm(u32),
+    LdAbs(Size, u32),
+    LdInd(Size, u32),
+    LdAncillary(Ancillary),
+    LdMem(usize),
+    LdLen,
+    LdxImm(u32),
+    LdxMem(usize),
+    LdxLen,
+    LdxMsh(u32),
+    St(usize),
+    Stx(usize),
+    Alu(AluOp, Src),
+    Neg,
+    Ja(u32),
+    Jmp(JmpOp, Src, u8, u8),
+    Ret(RetVal),
+    Tax,
+    Txa,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Size {
+    Word,
+    Half,
+    Byte,
+}
+
+impl Size {
+    fn len(self) -> usize {
+        match self {
+            Size::Word => 4,
+            Size::Half => 2,
+            Size::Byte => 1,
+        }
+    }
+}
+
+/// The data that can be loaded with the `SKF_AD_OFF` offset.
+#[derive(Debug, Clone, Copy)]
+enum Ancillary {
+    Protocol,
+    PktType,
+    IfIndex,
+    Mark,
+    Queue,
+    HaType,
+    RxHash,
+    VlanTag,
+    VlanTagPresent,
+}
+
+impl Ancillary {
+    fn decode(offset: i32) -> Option<Self> {
+        let ancillary = match offset.checked_sub(SKF_AD_OFF)? {
+            0 => Self::Protocol,
+            4 => Self::PktType,
+            8 => Self::IfIndex,
+            20 => Self::Mark,
+            24 => Self::Queue,
+            28 => Self::HaType,
+            32 => Self::RxHash,
+            44 => Self::VlanTag,
+            48 => Self::VlanTagPresent,
+            _ => return None,
+        };
+        Some(ancillary)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Src {
+    K(u32),
+    X,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AluOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Or,
+    And,
+    Lsh,
+    Rsh,
+    Mod,
+    Xor,
+}
+
+impl AluOp {
+    /// Applies the operation.
+    ///
+    /// This method returns `None` on division by zero.
+    fn apply(self, lhs: u32, rhs: u32) -> Option<u32> {
+        let val = match self {
+            AluOp::Add => lhs.wrapping_add(rhs),
+            AluOp::Sub => lhs.wrapping_sub(rhs),
+            AluOp::Mul => lhs.wrapping_mul(rhs),
+            AluOp::Div => lhs.checked_div(rhs)?,
+            AluOp::Or => lhs | rhs,
+            AluOp::And => lhs & rhs,
+            // Like Linux, shifting by 32 or more bits yields zero.
+            AluOp::Lsh => lhs.checked_shl(rhs).unwrap_or(0),
+            AluOp::Rsh => lhs.checked_shr(rhs).unwrap_or(0),
+            AluOp::Mod => lhs.checked_rem(rhs)?,
+            AluOp::Xor => lhs ^ rhs,
+        };
+        Some(val)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum JmpOp {
+    Jeq,
+    Jgt,
+    Jge,
+    Jset,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RetVal {
+    K(u32),
+    A,
+    X,
+}
+
+// Instruction classes
+const BPF_LD: u16 = 0x00;
+const BPF_LDX: u16 = 0x01;
+const BPF_ST: u16 = 0x02;
+const BPF_STX: u16 = 0x03;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_MISC: u16 = 0x07;
+
+// Sizes of `BPF_LD` and `BPF_LDX`
+const BPF_W: u16 = 0x00;
+const BPF_H: u16 = 0x08;
+const BPF_B: u16 = 0x10;
+
+// Modes of `BPF_LD` and `BPF_LDX`
+const BPF_IMM: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_IND: u16 = 0x40;
+const BPF_MEM: u16 = 0x60;
+const BPF_LEN: u16 = 0x80;
+const BPF_MSH: u16 = 0xa0;
+
+// Operations of `BPF_ALU`
+const BPF_ADD: u16 = 0x00;
+const BPF_SUB: u16 = 0x10;
+const BPF_MUL: u16 = 0x20;
+const BPF_DIV: u16 = 0x30;
+const BPF_OR: u16 = 0x40;
+const BPF_AND: u16 = 0x50;
+const BPF_LSH: u16 = 0x60;
+const BPF_RSH: u16 = 0x70;
+const BPF_NEG: u16 = 0x80;
+const BPF_MOD: u16 = 0x90;
+const BPF_XOR: u16 = 0xa0;
+
+// Operations of `BPF_JMP`
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_JSET: u16 = 0x40;
+
+// Sources of `BPF_ALU`, `BPF_JMP`, and `BPF_RET`
+const BPF_K: u16 = 0x00;
+const BPF_X: u16 = 0x08;
+const BPF_A: u16 = 0x10;
+
+// Operations of `BPF_MISC`
+const BPF_TAX: u16 = 0x00;
+const BPF_TXA: u16 = 0x80;
+
+impl Insn {
+    /// Decodes an instruction.
+    ///
+    /// `nr_insns_after` is the number of instructions after this instruction, which bounds the
+    /// jump offsets.
+    fn decode(c_insn: &CSockFilter, nr_insns_after: usize) -> Option<Self> {
+        let CSockFilter { code, jt, jf, k } = *c_insn;
+
+        let mem_idx = || ((k as usize) < NR_MEM_WORDS).then_some(k as usize);
+        let size = || match code & 0x18 {
+            BPF_W => Some(Size::Word),
+            BPF_H => Some(Size::Half),
+            BPF_B => Some(Size::Byte),
+            _ => None,
+        };
+        let src = || match code & 0x08 {
+            BPF_K => Src::K(k),
+            _ => Src::X,
+        };
+
+        if code > 0xff {
+            return None;
+        }
+
+        let insn = match code & 0x07 {
+            BPF_LD => match code & 0xe0 {
+                BPF_IMM if code == BPF_LD | BPF_W | BPF_IMM => Insn::LdImm(k),
+                BPF_ABS if (SKF_AD_OFF..0).contains(&(k as i32)) => {
+                    if code != BPF_LD | BPF_W | BPF_ABS {
+                        return None;
+                    }
+                    Insn::LdAncillary(Ancillary::decode(k as i32)?)
+                }
+                BPF_ABS => Insn::LdAbs(size()?, k),
+                BPF_IND => Insn::LdInd(size()?, k),
+                BPF_MEM if code == BPF_LD | BPF_W | BPF_MEM => Insn::LdMem(mem_idx()?),
+                BPF_LEN if code == BPF_LD | BPF_W | BPF_LEN => Insn::LdLen,
+                _ => return None,
+            },
+            BPF_LDX => match code & 0xe0 {
+                BPF_IMM if code == BPF_LDX | BPF_W | BPF_IMM => Insn::LdxImm(k),
+                BPF_MEM if code == BPF_LDX | BPF_W | BPF_MEM => Insn::LdxMem(mem_idx()?),
+                BPF_LEN if code == BPF_LDX | BPF_W | BPF_LEN => Insn::LdxLen,
+                BPF_MSH if code == BPF_LDX | BPF_B | BPF_MSH => Insn::LdxMsh(k),
+                _ => return None,
+            },
+            BPF_ST if code == BPF_ST => Insn::St(mem_idx()?),
+            BPF_STX if code == BPF_STX => Insn::Stx(mem_idx()?),
+            BPF_ALU => {
+                let op = match code & 0xf0 {
+                    BPF_ADD => AluOp::Add,
+                    BPF_SUB => AluOp::Sub,
+                    BPF_MUL => AluOp::Mul,
+                    BPF_DIV => AluOp::Div,
+                    BPF_OR => AluOp::Or,
+                    BPF_AND => AluOp::And,
+                    BPF_LSH => AluOp::Lsh,
+                    BPF_RSH => AluOp::Rsh,
+                    BPF_NEG if code == BPF_ALU | BPF_NEG => return Some(Insn::Neg),
+                    BPF_MOD => AluOp::Mod,
+                    BPF_XOR => AluOp::Xor,
+                    _ => return None,
+                };
+                // Like Linux, division by a constant zero is rejected in advance.
+                if code & 0x08 == BPF_K && k == 0 && matches!(op, AluOp::Div | AluOp::Mod) {
+                    return None;
+                }
+                Insn::Alu(op, src())
+            }
+            BPF_JMP => {
+                let op = match code & 0xf0 {
+                    BPF_JA if code == BPF_JMP | BPF_JA => {
+                        if k as usize >= nr_insns_after {
+                            return None;
+                        }
+                        return Some(Insn::Ja(k));
+                    }
+                    BPF_JEQ => JmpOp::Jeq,
+                    BPF_JGT => JmpOp::Jgt,
+                    BPF_JGE => JmpOp::Jge,
+                    BPF_JSET => JmpOp::Jset,
+                    _ => return None,
+                };
+                if jt as usize >= nr_insns_after || jf as usize >= nr_insns_after {
+                    return None;
+                }
+                Insn::Jmp(op, src(), jt, jf)
+            }
+            BPF_RET => match code & 0x18 {
+                BPF_K if code == BPF_RET | BPF_K => Insn::Ret(RetVal::K(k)),
+                BPF_X if code == BPF_RET | BPF_X => Insn::Ret(RetVal::X),
+                BPF_A if code == BPF_RET | BPF_A => Insn::Ret(RetVal::A),
+                _ => return None,
+            },
+            BPF_MISC => match code & 0xf8 {
+                BPF_TAX if code == BPF_MISC | BPF_TAX => Insn::Tax,
+                BPF_TXA if code == BPF_MISC | BPF_TXA => Insn::Txa,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(insn)
+    }
+}
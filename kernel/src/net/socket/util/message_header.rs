@@ -1,21 +1,30 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::socket_addr::SocketAddr;
-use crate::prelude::*;
+use core::mem::size_of;
+
+use align_ext::AlignExt;
+
+use super::{
+    error_queue::ErrorControlMessage, send_recv_flags::SendRecvFlags, socket_addr::SocketAddr,
+    timestamp::TimestampControlMessage,
+};
+use crate::{net::socket::unix::UnixControlMessage, prelude::*, util::net::CSocketOptionLevel};
 
 /// Message header used for sendmsg/recvmsg.
 #[derive(Debug)]
 pub struct MessageHeader {
     pub(in crate::net) addr: Option<SocketAddr>,
-    pub(in crate::net) control_message: Option<ControlMessage>,
+    pub(in crate::net) control_messages: Vec<ControlMessage>,
+    pub(in crate::net) is_truncated: bool,
 }
 
 impl MessageHeader {
     /// Creates a new `MessageHeader`.
-    pub const fn new(addr: Option<SocketAddr>, control_message: Option<ControlMessage>) -> Self {
+    pub const fn new(addr: Option<SocketAddr>, control_messages: Vec<ControlMessage>) -> Self {
         Self {
             addr,
-            control_message,
+            control_messages,
+            is_truncated: false,
         }
     }
 
@@ -23,10 +32,153 @@ impl MessageHeader {
     pub fn addr(&self) -> Option<&SocketAddr> {
         self.addr.as_ref()
     }
+
+    /// Returns whether the message is received from the error queue.
+    pub fn is_from_error_queue(&self) -> bool {
+        self.control_messages
+            .iter()
+            .any(|control_message| matches!(control_message, ControlMessage::Error(_)))
+    }
+
+    /// Marks that the message is truncated because the buffer is too small.
+    pub fn set_truncated(&mut self) {
+        self.is_truncated = true;
+    }
+
+    /// Returns whether the message is truncated because the buffer is too small.
+    pub fn is_truncated(&self) -> bool {
+        self.is_truncated
+    }
+
+    /// Consumes the message header and returns the control messages.
+    pub fn into_control_messages(self) -> Vec<ControlMessage> {
+        self.control_messages
+    }
 }
 
 /// Control message carried by MessageHeader.
-///
-/// TODO: Implement the struct. The struct is empty now.
 #[derive(Debug)]
-pub struct ControlMessage;
+pub enum ControlMessage {
+    Unix(UnixControlMessage),
+    Timestamp(TimestampControlMessage),
+    Error(ErrorControlMessage),
+}
+
+impl ControlMessage {
+    /// Reads all control messages from the reader.
+    ///
+    /// Control messages at levels that no socket understands are ignored, like Linux does.
+    pub fn read_all_from(reader: &mut VmReader, ctx: &Context) -> Result<Vec<Self>> {
+        let mut control_messages = Vec::new();
+
+        while reader.remain() >= size_of::<CControlHeader>() {
+            let header = reader.read_val::<CControlHeader>()?;
+            if header.len < size_of::<CControlHeader>() || header.payload_len() > reader.remain() {
+                return_errno_with_message!(Errno::EINVAL, "the control message length is invalid");
+            }
+
+            let mut payload_reader = reader.clone();
+            payload_reader.limit(header.payload_len());
+
+            match CSocketOptionLevel::try_from(header.level) {
+                Ok(CSocketOptionLevel::SOL_SOCKET) => {
+                    if let Some(message) =
+                        TimestampControlMessage::read_from(&header, &mut payload_reader)?
+                    {
+                        control_messages.push(ControlMessage::Timestamp(message));
+                    } else if let Some(message) =
+                        UnixControlMessage::read_from(&header, &mut payload_reader, ctx)?
+                    {
+                        control_messages.push(ControlMessage::Unix(message));
+                    }
+                }
+                _ => warn!("unsupported control message level: {}", header.level),
+            }
+
+            let skip_len = header.len.align_up(CONTROL_MESSAGE_ALIGN) - size_of::<CControlHeader>();
+            reader.skip(skip_len.min(reader.remain()));
+        }
+
+        Ok(control_messages)
+    }
+
+    /// Writes all control messages to the writer.
+    ///
+    /// Returns whether some control messages are truncated because the writer does not have
+    /// enough space.
+    pub fn write_all_to(
+        control_messages: Vec<Self>,
+        writer: &mut VmWriter,
+        flags: SendRecvFlags,
+        ctx: &Context,
+    ) -> Result<bool> {
+        let mut is_truncated = false;
+
+        for control_message in control_messages {
+            is_truncated |= match control_message {
+                ControlMessage::Unix(message) => message.write_to(writer, flags, ctx)?,
+                ControlMessage::Timestamp(message) => message.write_to(writer)?,
+                ControlMessage::Error(message) => message.write_to(writer)?,
+            };
+        }
+
+        Ok(is_truncated)
+    }
+}
+
+/// The header of a control message (i.e., `struct cmsghdr`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/socket.h#L95>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(in crate::net) struct CControlHeader {
+    /// The length of the control message, including the header
+    pub(in crate::net) len: usize,
+    /// The originating protocol
+    pub(in crate::net) level: i32,
+    /// The protocol-specific type
+    pub(in crate::net) type_: i32,
+}
+
+impl CControlHeader {
+    /// Returns the length of the payload that follows the header.
+    pub(in crate::net) fn payload_len(&self) -> usize {
+        self.len - size_of::<Self>()
+    }
+}
+
+/// The alignment of control messages (i.e., `CMSG_ALIGN`).
+const CONTROL_MESSAGE_ALIGN: usize = size_of::<usize>();
+
+/// Writes a control message with the payload to the writer.
+///
+/// If the writer does not have enough space, the payload will be truncated. Returns whether the
+/// control message is truncated.
+pub(in crate::net) fn write_control_message(
+    writer: &mut VmWriter,
+    level: CSocketOptionLevel,
+    type_: i32,
+    payload: &[u8],
+) -> Result<bool> {
+    const HEADER_LEN: usize = size_of::<CControlHeader>();
+
+    if writer.avail() < HEADER_LEN {
+        return Ok(true);
+    }
+
+    let full_len = HEADER_LEN + payload.len();
+    let len = full_len.min(writer.avail());
+
+    let header = CControlHeader {
+        len,
+        level: level as i32,
+        type_,
+    };
+    writer.write_val(&header)?;
+    writer.write_fallible(&mut VmReader::from(&payload[..len - HEADER_LEN]))?;
+
+    let padding_len = len.align_up(CONTROL_MESSAGE_ALIGN) - len;
+    writer.skip(padding_len.min(writer.avail()));
+
+    Ok(len < full_len)
+}
@@ -1,10 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub mod datagram_common;
+pub mod error_queue;
+pub mod filter;
 mod message_header;
 pub mod options;
 pub mod send_recv_flags;
 pub mod shutdown_cmd;
 pub mod socket_addr;
+pub mod timestamp;
 
-pub use message_header::MessageHeader;
+pub(in crate::net) use message_header::{write_control_message, CControlHeader};
+pub use message_header::{ControlMessage, MessageHeader};
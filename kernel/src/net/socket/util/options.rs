@@ -6,10 +6,12 @@ use aster_bigtcp::socket::{
     NeedIfacePoll, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
 };
 
+use super::timestamp::{TimestampFormat, TimestampingFlags};
 use crate::{
     match_sock_option_mut, match_sock_option_ref,
     net::socket::options::{
-        KeepAlive, Linger, RecvBuf, ReuseAddr, ReusePort, SendBuf, SocketOption,
+        KeepAlive, Linger, RecvBuf, ReuseAddr, ReusePort, SendBuf, SocketOption, Timestamp,
+        TimestampNs, Timestamping, ZeroCopy,
     },
     prelude::*,
 };
@@ -24,6 +26,9 @@ pub struct SocketOptionSet {
     recv_buf: u32,
     linger: LingerOption,
     keep_alive: bool,
+    timestamp_format: Option<TimestampFormat>,
+    timestamping: TimestampingFlags,
+    zerocopy: bool,
 }
 
 impl SocketOptionSet {
@@ -36,6 +41,9 @@ impl SocketOptionSet {
             recv_buf: TCP_RECV_BUF_LEN as u32,
             linger: LingerOption::default(),
             keep_alive: false,
+            timestamp_format: None,
+            timestamping: TimestampingFlags::empty(),
+            zerocopy: false,
         }
     }
 
@@ -48,6 +56,9 @@ impl SocketOptionSet {
             recv_buf: UDP_RECV_PAYLOAD_LEN as u32,
             linger: LingerOption::default(),
             keep_alive: false,
+            timestamp_format: None,
+            timestamping: TimestampingFlags::empty(),
+            zerocopy: false,
         }
     }
 
@@ -82,6 +93,22 @@ impl SocketOptionSet {
                 let keep_alive = self.keep_alive();
                 socket_keepalive.set(keep_alive);
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = self.timestamp_format() == Some(TimestampFormat::Timeval);
+                socket_timestamp.set(timestamp);
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp_ns = self.timestamp_format() == Some(TimestampFormat::Timespec);
+                socket_timestamp_ns.set(timestamp_ns);
+            },
+            socket_timestamping: Timestamping => {
+                let timestamping = self.timestamping();
+                socket_timestamping.set(timestamping);
+            },
+            socket_zerocopy: ZeroCopy => {
+                let zerocopy = self.zerocopy();
+                socket_zerocopy.set(zerocopy);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
         });
         Ok(())
@@ -127,6 +154,28 @@ impl SocketOptionSet {
                 self.set_keep_alive(*keep_alive);
                 return Ok(socket.set_keep_alive(*keep_alive));
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = socket_timestamp.get().unwrap();
+                self.set_timestamp_format(timestamp.then_some(TimestampFormat::Timeval));
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp_ns = socket_timestamp_ns.get().unwrap();
+                self.set_timestamp_format(timestamp_ns.then_some(TimestampFormat::Timespec));
+            },
+            socket_timestamping: Timestamping => {
+                let timestamping = socket_timestamping.get().unwrap();
+                timestamping.check()?;
+                if timestamping.contains(TimestampingFlags::OPT_ID)
+                    && !self.timestamping().contains(TimestampingFlags::OPT_ID)
+                {
+                    socket.reset_timestamp_key();
+                }
+                self.set_timestamping(*timestamping);
+            },
+            socket_zerocopy: ZeroCopy => {
+                let zerocopy = socket_zerocopy.get().unwrap();
+                self.set_zerocopy(*zerocopy);
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
         });
 
@@ -163,4 +212,7 @@ pub(in crate::net) trait SetSocketLevelOption {
     fn set_keep_alive(&self, _keep_alive: bool) -> NeedIfacePoll {
         NeedIfacePoll::FALSE
     }
+
+    /// Resets the key of the transmit timestamps, because `SOF_TIMESTAMPING_OPT_ID` is enabled.
+    fn reset_timestamp_key(&self) {}
 }
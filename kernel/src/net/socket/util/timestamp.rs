@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Timestamps of packets.
+//!
+//! The time at which a packet is received can be reported with `SO_TIMESTAMP`, `SO_TIMESTAMPNS`,
+//! or `SO_TIMESTAMPING`. The time at which a packet is sent can be reported with
+//! `SO_TIMESTAMPING`, which puts the timestamps in the error queue.
+//!
+//! Only software timestamps are supported, since no devices support hardware timestamps. Hardware
+//! timestamps can still be requested, but they are never generated.
+//!
+//! Reference: <https://docs.kernel.org/networking/timestamping.html>.
+
+use core::{mem::size_of, time::Duration};
+
+use aster_bigtcp::time::PacketClock;
+
+use super::{options::SocketOptionSet, write_control_message, CControlHeader};
+use crate::{
+    net::socket::ControlMessage,
+    prelude::*,
+    time::{clocks::RealTimeClock, timespec_t, timeval_t, Clock},
+    util::net::CSocketOptionLevel,
+};
+
+bitflags! {
+    /// Flags of `SO_TIMESTAMPING`.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/net_tstamp.h#L17>.
+    pub struct TimestampingFlags: u32 {
+        const TX_HARDWARE   = 1 << 0;
+        const TX_SOFTWARE   = 1 << 1;
+        const RX_HARDWARE   = 1 << 2;
+        const RX_SOFTWARE   = 1 << 3;
+        const SOFTWARE      = 1 << 4;
+        const SYS_HARDWARE  = 1 << 5;
+        const RAW_HARDWARE  = 1 << 6;
+        const OPT_ID        = 1 << 7;
+        const TX_SCHED      = 1 << 8;
+        const TX_ACK        = 1 << 9;
+        const OPT_CMSG      = 1 << 10;
+        const OPT_TSONLY    = 1 << 11;
+        const OPT_STATS     = 1 << 12;
+        const OPT_PKTINFO   = 1 << 13;
+        const OPT_TX_SWHW   = 1 << 14;
+        const BIND_PHC      = 1 << 15;
+        const OPT_ID_TCP    = 1 << 16;
+        const OPT_RX_FILTER = 1 << 17;
+    }
+}
+
+impl TimestampingFlags {
+    /// The flags that request the transmit timestamps (i.e., `SOF_TIMESTAMPING_TX_RECORD_MASK`).
+    pub const TX_RECORD_MASK: Self = Self::TX_HARDWARE
+        .union(Self::TX_SOFTWARE)
+        .union(Self::TX_SCHED)
+        .union(Self::TX_ACK);
+
+    /// Checks whether the flags are valid to be set with `SO_TIMESTAMPING`.
+    pub(in crate::net) fn check(&self) -> Result<()> {
+        if self.contains(Self::OPT_ID_TCP) && !self.contains(Self::OPT_ID) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "SOF_TIMESTAMPING_OPT_ID_TCP requires SOF_TIMESTAMPING_OPT_ID"
+            );
+        }
+
+        if self.contains(Self::OPT_STATS) && !self.contains(Self::OPT_TSONLY) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "SOF_TIMESTAMPING_OPT_STATS requires SOF_TIMESTAMPING_OPT_TSONLY"
+            );
+        }
+
+        if self.contains(Self::BIND_PHC) {
+            return_errno_with_message!(Errno::EINVAL, "no PTP hardware clocks are available");
+        }
+
+        Ok(())
+    }
+}
+
+/// The format of the receive timestamps enabled by `SO_TIMESTAMP` or `SO_TIMESTAMPNS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// The timestamps in microseconds (`SO_TIMESTAMP`).
+    Timeval,
+    /// The timestamps in nanoseconds (`SO_TIMESTAMPNS`).
+    Timespec,
+}
+
+/// The clock that timestamps the packets.
+///
+/// Like Linux, the packets are timestamped with the real-time clock.
+pub struct RealTimePacketClock;
+
+impl PacketClock for RealTimePacketClock {
+    fn now() -> Duration {
+        RealTimeClock::get().read_time()
+    }
+}
+
+/// A control message that carries timestamps.
+#[derive(Debug)]
+pub enum TimestampControlMessage {
+    /// The receive timestamp passed with `SCM_TIMESTAMP`.
+    Timeval(timeval_t),
+    /// The receive timestamp passed with `SCM_TIMESTAMPNS`.
+    Timespec(timespec_t),
+    /// The timestamps passed with `SCM_TIMESTAMPING`.
+    Timestamping(CScmTimestamping),
+    /// The flags passed with `SCM_TIMESTAMPING` to request transmit timestamps for a message.
+    TxFlags(TimestampingFlags),
+}
+
+/// The types of socket-level control messages about timestamps.
+///
+/// The types are the same as the corresponding socket options.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/asm-generic/socket.h#L140>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+enum CControlType {
+    SCM_TIMESTAMP = 29,
+    SCM_TIMESTAMPNS = 35,
+    SCM_TIMESTAMPING = 37,
+}
+
+/// The timestamps of a packet (i.e., `struct scm_timestamping`).
+///
+/// The first timestamp is the software timestamp, and the last timestamp is the hardware
+/// timestamp. The middle one is deprecated and always zero.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/errqueue.h#L45>.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+pub struct CScmTimestamping {
+    ts: [timespec_t; 3],
+}
+
+impl TimestampControlMessage {
+    /// Reads the payload of a socket-level control message from the reader.
+    ///
+    /// Returns `None` if the control message is not about timestamps.
+    pub(in crate::net) fn read_from(
+        header: &CControlHeader,
+        reader: &mut VmReader,
+    ) -> Result<Option<Self>> {
+        let Ok(type_) = CControlType::try_from(header.type_) else {
+            return Ok(None);
+        };
+
+        match type_ {
+            CControlType::SCM_TIMESTAMPING => {
+                if header.payload_len() != size_of::<u32>() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the timestamping control message length is invalid"
+                    );
+                }
+
+                let flags = TimestampingFlags::from_bits(reader.read_val::<u32>()?)
+                    .filter(|flags| TimestampingFlags::TX_RECORD_MASK.contains(*flags))
+                    .ok_or_else(|| {
+                        Error::with_message(Errno::EINVAL, "the timestamping flags are invalid")
+                    })?;
+
+                Ok(Some(Self::TxFlags(flags)))
+            }
+            CControlType::SCM_TIMESTAMP | CControlType::SCM_TIMESTAMPNS => {
+                return_errno_with_message!(Errno::EINVAL, "the control message type is invalid")
+            }
+        }
+    }
+
+    /// Writes the control message to the writer.
+    ///
+    /// Returns whether the control message is truncated.
+    pub(in crate::net) fn write_to(self, writer: &mut VmWriter) -> Result<bool> {
+        let (type_, payload) = match &self {
+            Self::Timeval(timeval) => (CControlType::SCM_TIMESTAMP, timeval.as_bytes()),
+            Self::Timespec(timespec) => (CControlType::SCM_TIMESTAMPNS, timespec.as_bytes()),
+            Self::Timestamping(timestamping) => {
+                (CControlType::SCM_TIMESTAMPING, timestamping.as_bytes())
+            }
+            // The flags are only passed to the kernel, never to the user.
+            Self::TxFlags(_) => return Ok(false),
+        };
+
+        write_control_message(
+            writer,
+            CSocketOptionLevel::SOL_SOCKET,
+            type_ as i32,
+            payload,
+        )
+    }
+}
+
+/// Returns the control messages that report the timestamp of a packet.
+///
+/// The packet is a received packet, or a sent packet in the error queue if `is_error_queue` is
+/// true.
+pub(in crate::net) fn timestamp_control_messages(
+    options: &SocketOptionSet,
+    timestamp: Duration,
+    is_error_queue: bool,
+) -> Vec<ControlMessage> {
+    let mut control_messages = Vec::new();
+
+    match options.timestamp_format() {
+        Some(TimestampFormat::Timeval) => control_messages.push(ControlMessage::Timestamp(
+            TimestampControlMessage::Timeval(timestamp.into()),
+        )),
+        Some(TimestampFormat::Timespec) => control_messages.push(ControlMessage::Timestamp(
+            TimestampControlMessage::Timespec(timestamp.into()),
+        )),
+        None => (),
+    }
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.13/source/net/socket.c#L925>.
+    let flags = options.timestamping();
+    if flags.contains(TimestampingFlags::SOFTWARE)
+        && (flags.contains(TimestampingFlags::RX_SOFTWARE)
+            || is_error_queue
+            || !flags.contains(TimestampingFlags::OPT_RX_FILTER))
+    {
+        let mut timestamping = CScmTimestamping::default();
+        timestamping.ts[0] = timestamp.into();
+        control_messages.push(ControlMessage::Timestamp(
+            TimestampControlMessage::Timestamping(timestamping),
+        ));
+    }
+
+    control_messages
+}
@@ -16,6 +16,7 @@ use crate::{
     cpu::LinuxAbi,
     current_userspace,
     fs::{file_table::FileTable, thread_info::ThreadFsInfo},
+    net::socket::netlink::proc_events,
     prelude::*,
     process::posix_thread::allocate_posix_tid,
     sched::Nice,
@@ -191,15 +192,28 @@ pub fn clone_child(
     if clone_args.flags.contains(CloneFlags::CLONE_THREAD) {
         let child_task = clone_child_task(ctx, parent_context, clone_args)?;
         let child_thread = child_task.as_thread().unwrap();
+        let child_tid = child_thread.as_posix_thread().unwrap().tid();
+        proc_events::on_fork(
+            ctx.posix_thread.tid(),
+            ctx.process.pid(),
+            child_tid,
+            ctx.process.pid(),
+        );
+
         child_thread.run();
 
-        let child_tid = child_thread.as_posix_thread().unwrap().tid();
         Ok(child_tid)
     } else {
         let child_process = clone_child_process(ctx, parent_context, clone_args)?;
         if clone_args.flags.contains(CloneFlags::CLONE_VFORK) {
             child_process.status().set_vfork_child(true);
         }
+        proc_events::on_fork(
+            ctx.posix_thread.tid(),
+            ctx.process.pid(),
+            child_process.pid(),
+            child_process.pid(),
+        );
 
         child_process.run();
 
@@ -268,7 +282,8 @@ fn clone_child_task(
             .process(posix_thread.weak_process())
             .sig_mask(sig_mask)
             .file_table(child_file_table)
-            .fs(child_fs);
+            .fs(child_fs)
+            .core_cookie(ctx.thread.sched_attr().core_cookie());
 
         // Deal with SETTID/CLEARTID flags
         clone_parent_settid(child_tid, clone_args.parent_tid, clone_flags)?;
@@ -354,6 +369,7 @@ fn clone_child_process(
                 .sig_mask(child_sig_mask)
                 .file_table(child_file_table)
                 .fs(child_fs)
+                .core_cookie(ctx.thread.sched_attr().core_cookie())
         };
 
         // Deal with SETTID/CLEARTID flags
@@ -379,6 +395,17 @@ fn clone_child_process(
         child.set_exit_signal(sig);
     };
 
+    // The child shares the dumpable attribute since its memory is copied from the parent.
+    child.set_dumpable(process.is_dumpable());
+
+    // Inherit the parent's syscall deny list
+    child
+        .syscall_deny_list()
+        .copy_from(process.syscall_deny_list());
+
+    // The child has the same credentials as the current thread.
+    child.update_vdso_identity(&ctx.posix_thread.credentials());
+
     // Sets parent process and group for child process.
     set_parent_and_group(process, &child);
 
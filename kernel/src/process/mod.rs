@@ -23,8 +23,8 @@ pub use clone::{clone_child, CloneArgs, CloneFlags};
 pub use credentials::{Credentials, Gid, Uid};
 pub use kill::{kill, kill_all, kill_group, tgkill};
 pub use process::{
-    spawn_init_process, ExitCode, JobControl, Pgid, Pid, Process, ProcessGroup, Session, Sid,
-    Terminal,
+    spawn_init_process, ExitCode, JobControl, Pgid, Pid, PosixTimer, Process, ProcessGroup,
+    Session, Sid, Terminal,
 };
 pub use process_filter::ProcessFilter;
 pub use process_vm::{
@@ -1,12 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::AtomicBool;
+
 use ostd::{
     cpu::{context::UserContext, CpuSet},
     sync::RwArc,
     task::Task,
 };
 
-use super::{thread_table, PosixThread, ThreadLocal};
+use super::{thread_table, PosixThread, SyscallInfo, ThreadLocal};
 use crate::{
     fs::{file_table::FileTable, thread_info::ThreadFsInfo},
     prelude::*,
@@ -37,6 +39,7 @@ pub struct PosixThreadBuilder {
     sig_mask: AtomicSigMask,
     sig_queues: SigQueues,
     sched_policy: SchedPolicy,
+    core_cookie: u64,
 }
 
 impl PosixThreadBuilder {
@@ -54,6 +57,7 @@ impl PosixThreadBuilder {
             sig_mask: AtomicSigMask::new_empty(),
             sig_queues: SigQueues::new(),
             sched_policy: SchedPolicy::Fair(Nice::default()),
+            core_cookie: 0,
         }
     }
 
@@ -92,6 +96,11 @@ impl PosixThreadBuilder {
         self
     }
 
+    pub fn core_cookie(mut self, core_cookie: u64) -> Self {
+        self.core_cookie = core_cookie;
+        self
+    }
+
     pub fn build(self) -> Arc<Task> {
         let Self {
             tid,
@@ -106,6 +115,7 @@ impl PosixThreadBuilder {
             sig_mask,
             sig_queues,
             sched_policy,
+            core_cookie,
         } = self;
 
         let file_table = file_table.unwrap_or_else(|| RwArc::new(FileTable::new_with_stdio()));
@@ -136,6 +146,8 @@ impl PosixThreadBuilder {
                     sig_mask,
                     sig_queues,
                     signalled_waker: SpinLock::new(None),
+                    is_interruptible_wait: AtomicBool::new(false),
+                    syscall_info: SyscallInfo::new(),
                     prof_clock,
                     virtual_timer_manager,
                     prof_timer_manager,
@@ -149,6 +161,7 @@ impl PosixThreadBuilder {
                 cpu_affinity,
                 sched_policy,
             ));
+            thread.sched_attr().set_core_cookie(core_cookie);
 
             let thread_local =
                 ThreadLocal::new(set_child_tid, clear_child_tid, root_vmar, file_table);
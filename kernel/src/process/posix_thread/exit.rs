@@ -8,6 +8,7 @@ use super::{
 };
 use crate::{
     current_userspace,
+    net::socket::netlink::proc_events,
     prelude::*,
     process::{
         exit::exit_process,
@@ -86,6 +87,23 @@ fn exit_internal(term_status: TermStatus, is_exiting_group: bool) {
     *thread_local.root_vmar().borrow_mut() = None;
     thread_local.borrow_file_table_mut().remove();
 
+    let tid = posix_thread.tid();
+    let pid = posix_process.pid();
+    let exit_signal = if tid == pid {
+        posix_process.exit_signal()
+    } else {
+        None
+    };
+    let parent_pid = posix_process.parent().pid();
+    proc_events::on_exit(
+        tid,
+        pid,
+        posix_process.status().exit_code(),
+        exit_signal,
+        parent_pid,
+        parent_pid,
+    );
+
     if is_last_thread {
         exit_process(&posix_process);
     }
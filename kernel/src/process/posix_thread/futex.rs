@@ -6,7 +6,11 @@ use ostd::{
 };
 use spin::Once;
 
-use crate::{prelude::*, process::Pid, time::wait::ManagedTimeout};
+use crate::{
+    prelude::*,
+    process::Pid,
+    time::wait::{ManagedTimeout, TimeoutExt},
+};
 
 type FutexBitSet = u32;
 
@@ -14,6 +18,11 @@ const FUTEX_OP_MASK: u32 = 0x0000_000F;
 const FUTEX_FLAGS_MASK: u32 = 0xFFFF_FFF0;
 const FUTEX_BITSET_MATCH_ANY: FutexBitSet = 0xFFFF_FFFF;
 
+const FUTEX2_SIZE_MASK: u32 = 0x3;
+
+/// The maximum number of futexes that can be waited on by [`futex_waitv`].
+pub const FUTEX_WAITV_MAX: usize = 128;
+
 /// do futex wait
 pub fn futex_wait(
     futex_addr: u64,
@@ -88,6 +97,90 @@ pub fn futex_wait_bitset(
     // to exhaust kernel memory.
 }
 
+/// A futex to wait on with [`futex_waitv`].
+#[derive(Debug, Clone, Copy)]
+pub struct FutexWaitv {
+    pub addr: Vaddr,
+    pub val: u64,
+    pub size: FutexSize,
+    pub pid: Option<Pid>,
+}
+
+/// Does futex wait on multiple futexes.
+///
+/// The current thread sleeps until one of the futexes is woken up, and the index of the woken
+/// futex is returned. If more than one futex is woken up, the smallest index is returned.
+pub fn futex_waitv(
+    futexes: &[FutexWaitv],
+    timeout: Option<ManagedTimeout>,
+    ctx: &Context,
+) -> Result<usize> {
+    debug!("futex_waitv futexes: {:?}", futexes);
+
+    let keys = futexes
+        .iter()
+        .map(|futex| FutexKey::new(futex.addr, FUTEX_BITSET_MATCH_ANY, futex.pid))
+        .collect::<Vec<_>>();
+    let timeout = TimeoutExt::from(timeout);
+
+    loop {
+        let (waiter, waker) = Waiter::new_pair();
+
+        for (index, (futex, futex_key)) in futexes.iter().zip(keys.iter()).enumerate() {
+            let (_, futex_bucket_ref) = get_futex_bucket(*futex_key);
+            // lock futex bucket ref here to avoid data race
+            let mut futex_bucket = futex_bucket_ref.lock();
+
+            if !futex_key
+                .load_sized_val(futex.size, ctx)
+                .is_ok_and(|val| val == futex.val)
+            {
+                drop(futex_bucket);
+                // The futexes that have been enqueued may have been woken up in the meantime.
+                if let Some(woken_index) = dequeue_multiple(&keys[..index], &waker) {
+                    return Ok(woken_index);
+                }
+                return_errno_with_message!(
+                    Errno::EAGAIN,
+                    "futex value does not match or load_val failed"
+                );
+            }
+
+            futex_bucket.add_item(FutexItem {
+                key: *futex_key,
+                waker: waker.clone(),
+            });
+        }
+
+        let result = waiter.pause_timeout(&timeout);
+
+        if let Some(woken_index) = dequeue_multiple(&keys, &waker) {
+            return Ok(woken_index);
+        }
+        // No futex is woken up, so the wakeup is caused by a timeout, a signal, or a spurious
+        // wakeup. Only the last case goes on to wait again.
+        result?;
+    }
+}
+
+/// Dequeues the futex items of `waker` for all the keys.
+///
+/// Returns the index of the first key whose futex item has already been dequeued, which means
+/// that the futex has been woken up.
+fn dequeue_multiple(keys: &[FutexKey], waker: &Arc<Waker>) -> Option<usize> {
+    let mut woken_index = None;
+
+    for (index, futex_key) in keys.iter().enumerate() {
+        let (_, futex_bucket_ref) = get_futex_bucket(*futex_key);
+        let is_dequeued = futex_bucket_ref.lock().remove_item(*futex_key, waker);
+        if !is_dequeued && woken_index.is_none() {
+            woken_index = Some(index);
+        }
+    }
+
+    woken_index
+}
+
 /// Does futex wake
 pub fn futex_wake(futex_addr: Vaddr, max_count: usize, pid: Option<Pid>) -> Result<usize> {
     futex_wake_bitset(futex_addr, max_count, FUTEX_BITSET_MATCH_ANY, pid)
@@ -251,6 +344,19 @@ impl FutexBucket {
         count
     }
 
+    pub fn remove_item(&mut self, key: FutexKey, waker: &Arc<Waker>) -> bool {
+        let Some(pos) = self
+            .items
+            .iter()
+            .position(|item| item.key.match_up(&key) && Arc::ptr_eq(&item.waker, waker))
+        else {
+            return false;
+        };
+
+        self.items.swap_remove(pos);
+        true
+    }
+
     pub fn update_item_keys(&mut self, key: FutexKey, new_key: FutexKey, max_count: usize) {
         let mut count = 0;
         for item in self.items.iter_mut() {
@@ -328,6 +434,18 @@ impl FutexKey {
         ctx.user_space().read_val(self.addr)
     }
 
+    pub fn load_sized_val(&self, size: FutexSize, ctx: &Context) -> Result<u64> {
+        // FIXME: how to implement a atomic load?
+        let user_space = ctx.user_space();
+        let val = match size {
+            FutexSize::U8 => user_space.read_val::<u8>(self.addr)? as u64,
+            FutexSize::U16 => user_space.read_val::<u16>(self.addr)? as u64,
+            FutexSize::U32 => user_space.read_val::<u32>(self.addr)? as u64,
+            FutexSize::U64 => user_space.read_val::<u64>(self.addr)?,
+        };
+        Ok(val)
+    }
+
     pub fn addr(&self) -> Vaddr {
         self.addr
     }
@@ -389,6 +507,48 @@ impl FutexFlags {
     }
 }
 
+/// The size of a futex word.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FutexSize {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl FutexSize {
+    /// Returns the size in bytes.
+    pub fn nbytes(self) -> usize {
+        match self {
+            FutexSize::U8 => 1,
+            FutexSize::U16 => 2,
+            FutexSize::U32 => 4,
+            FutexSize::U64 => 8,
+        }
+    }
+
+    /// Returns the maximum value that fits in the futex word.
+    pub fn max_val(self) -> u64 {
+        u64::MAX >> (u64::BITS as usize - self.nbytes() * 8)
+    }
+}
+
+/// Parses the flags of a futex2 operation (e.g., the flags of each futex in `futex_waitv`).
+pub fn futex2_size_and_flags_from_u32(bits: u32) -> Result<(FutexSize, FutexFlags)> {
+    let size = match bits & FUTEX2_SIZE_MASK {
+        0 => FutexSize::U8,
+        1 => FutexSize::U16,
+        2 => FutexSize::U32,
+        _ => FutexSize::U64,
+    };
+    // Only `FUTEX2_PRIVATE`, which has the same value as `FUTEX_PRIVATE`, is supported.
+    // `FUTEX2_NUMA` is not supported.
+    let flags = FutexFlags::from_bits(bits & !FUTEX2_SIZE_MASK)
+        .filter(|flags| !flags.contains(FutexFlags::FUTEX_CLOCK_REALTIME))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown futex2 flags"))?;
+    Ok((size, flags))
+}
+
 pub fn futex_op_and_flags_from_u32(bits: u32) -> Result<(FutexOp, FutexFlags)> {
     let op = {
         let op_bits = bits & FUTEX_OP_MASK;
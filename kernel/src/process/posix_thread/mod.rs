@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use aster_rights::{ReadOp, WriteOp};
 use ostd::sync::{RoArc, Waker};
@@ -32,6 +32,7 @@ pub mod futex;
 mod name;
 mod posix_thread_ext;
 mod robust_list;
+mod syscall_info;
 mod thread_local;
 pub mod thread_table;
 
@@ -40,6 +41,7 @@ pub use exit::{do_exit, do_exit_group};
 pub use name::{ThreadName, MAX_THREAD_NAME_LEN};
 pub use posix_thread_ext::AsPosixThread;
 pub use robust_list::RobustListHead;
+pub use syscall_info::{SyscallInfo, SyscallRecord};
 pub use thread_local::{AsThreadLocal, FileTableRefMut, ThreadLocal};
 
 pub struct PosixThread {
@@ -67,6 +69,13 @@ pub struct PosixThread {
     /// The per-thread signal [`Waker`], which will be used to wake up the thread
     /// when enqueuing a signal.
     signalled_waker: SpinLock<Option<Arc<Waker>>>,
+    /// Whether the thread is waiting in a way that can be interrupted by signals.
+    ///
+    /// This mirrors whether the signalled waker is set, but can be read without locking.
+    is_interruptible_wait: AtomicBool,
+
+    /// The system call that the thread is executing.
+    syscall_info: SyscallInfo,
 
     /// A profiling clock measures the user CPU time and kernel CPU time in the thread.
     prof_clock: Arc<ProfClock>,
@@ -104,6 +113,11 @@ impl PosixThread {
         &self.fs
     }
 
+    /// Returns the system call that the thread is executing.
+    pub fn syscall_info(&self) -> &SyscallInfo {
+        &self.syscall_info
+    }
+
     /// Get the reference to the signal mask of the thread.
     ///
     /// Note that while this function offers mutable access to the signal mask,
@@ -192,13 +206,23 @@ impl PosixThread {
         let mut signalled_waker = self.signalled_waker.lock();
         assert!(signalled_waker.is_none());
         *signalled_waker = Some(waker);
+        self.is_interruptible_wait.store(true, Ordering::Relaxed);
     }
 
     /// Clears the signalled waker of this thread.
     pub fn clear_signalled_waker(&self) {
+        self.is_interruptible_wait.store(false, Ordering::Relaxed);
         *self.signalled_waker.lock() = None;
     }
 
+    /// Returns whether the thread is waiting in a way that can be interrupted by signals.
+    ///
+    /// A sleeping thread that is not waiting interruptibly is in the uninterruptible
+    /// sleep, which is counted in the system load.
+    pub fn is_interruptible_wait(&self) -> bool {
+        self.is_interruptible_wait.load(Ordering::Relaxed)
+    }
+
     /// Enqueues a thread-directed signal. This method should only be used for enqueue kernel
     /// signal and fault signal.
     pub fn enqueue_signal(&self, signal: Box<dyn Signal>) {
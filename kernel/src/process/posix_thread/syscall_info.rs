@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The system call that a thread is executing.
+///
+/// The information is only written by the thread itself, but can be read by
+/// others (e.g., via `/proc/[pid]/syscall`). The fields are accessed
+/// individually, so a reader may see a mix of two consecutive system calls.
+/// This is acceptable since the information is only used for diagnosis.
+pub struct SyscallInfo {
+    /// The system call number, or [`Self::NO_SYSCALL`].
+    num: AtomicUsize,
+    args: [AtomicUsize; 6],
+    /// The user stack pointer when the system call is made.
+    stack_pointer: AtomicUsize,
+    /// The user instruction pointer when the system call is made.
+    instruction_pointer: AtomicUsize,
+}
+
+/// A snapshot of [`SyscallInfo`].
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallRecord {
+    /// The system call number and the arguments, or `None` if the thread is
+    /// not executing a system call.
+    pub syscall: Option<(usize, [usize; 6])>,
+    pub stack_pointer: usize,
+    pub instruction_pointer: usize,
+}
+
+impl SyscallInfo {
+    const NO_SYSCALL: usize = usize::MAX;
+
+    pub(super) fn new() -> Self {
+        Self {
+            num: AtomicUsize::new(Self::NO_SYSCALL),
+            args: Default::default(),
+            stack_pointer: AtomicUsize::new(0),
+            instruction_pointer: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records that the thread enters a system call.
+    pub fn enter(
+        &self,
+        num: usize,
+        args: &[usize; 6],
+        stack_pointer: usize,
+        instruction_pointer: usize,
+    ) {
+        for (arg, val) in self.args.iter().zip(args) {
+            arg.store(*val, Ordering::Relaxed);
+        }
+        self.stack_pointer.store(stack_pointer, Ordering::Relaxed);
+        self.instruction_pointer
+            .store(instruction_pointer, Ordering::Relaxed);
+        self.num.store(num, Ordering::Relaxed);
+    }
+
+    /// Records that the thread leaves the system call.
+    pub fn exit(&self) {
+        self.num.store(Self::NO_SYSCALL, Ordering::Relaxed);
+    }
+
+    /// Reads the information.
+    pub fn read(&self) -> SyscallRecord {
+        let num = self.num.load(Ordering::Relaxed);
+        let syscall = (num != Self::NO_SYSCALL).then(|| {
+            (
+                num,
+                self.args.each_ref().map(|arg| arg.load(Ordering::Relaxed)),
+            )
+        });
+
+        SyscallRecord {
+            syscall,
+            stack_pointer: self.stack_pointer.load(Ordering::Relaxed),
+            instruction_pointer: self.instruction_pointer.load(Ordering::Relaxed),
+        }
+    }
+}
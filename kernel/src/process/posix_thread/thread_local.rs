@@ -6,7 +6,12 @@ use aster_rights::Full;
 use ostd::{mm::Vaddr, sync::RwArc, task::CurrentTask};
 
 use super::RobustListHead;
-use crate::{fs::file_table::FileTable, process::signal::SigStack, vm::vmar::Vmar};
+use crate::{
+    fs::file_table::{FdArray, FileTable},
+    prelude::*,
+    process::signal::{sig_mask::SigMask, SigFrameLayout, SigStack},
+    vm::vmar::Vmar,
+};
 
 /// Local data for a POSIX thread.
 pub struct ThreadLocal {
@@ -23,7 +28,7 @@ pub struct ThreadLocal {
     robust_list: RefCell<Option<RobustListHead>>,
 
     // Files.
-    file_table: RefCell<Option<RwArc<FileTable>>>,
+    file_table: RefCell<Option<LocalFileTable>>,
 
     // Signal.
     /// `ucontext` address for the signal handler.
@@ -32,6 +37,15 @@ pub struct ThreadLocal {
     sig_context: Cell<Option<Vaddr>>,
     /// Stack address, size, and flags for the signal handler.
     sig_stack: RefCell<Option<SigStack>>,
+    /// The layout of the signal frames, which is computed on the first signal delivery.
+    sig_frame_layout: Cell<Option<SigFrameLayout>>,
+    /// The signal mask to restore after the pending signals are handled.
+    ///
+    /// This is set if a syscall that replaces the signal mask temporarily (e.g., `ppoll`) is
+    /// interrupted by a signal. See [`with_sigmask_replaced`] for details.
+    ///
+    /// [`with_sigmask_replaced`]: crate::process::signal::with_sigmask_replaced
+    saved_sig_mask: Cell<Option<SigMask>>,
 }
 
 impl ThreadLocal {
@@ -46,9 +60,11 @@ impl ThreadLocal {
             clear_child_tid: Cell::new(clear_child_tid),
             root_vmar: RefCell::new(Some(root_vmar)),
             robust_list: RefCell::new(None),
-            file_table: RefCell::new(Some(file_table)),
+            file_table: RefCell::new(Some(LocalFileTable::new(file_table))),
             sig_context: Cell::new(None),
             sig_stack: RefCell::new(None),
+            sig_frame_layout: Cell::new(None),
+            saved_sig_mask: Cell::new(None),
         }
     }
 
@@ -83,10 +99,33 @@ impl ThreadLocal {
     pub fn sig_stack(&self) -> &RefCell<Option<SigStack>> {
         &self.sig_stack
     }
+
+    pub fn sig_frame_layout(&self) -> &Cell<Option<SigFrameLayout>> {
+        &self.sig_frame_layout
+    }
+
+    pub fn saved_sig_mask(&self) -> &Cell<Option<SigMask>> {
+        &self.saved_sig_mask
+    }
+}
+
+/// The file table in [`ThreadLocal`].
+struct LocalFileTable {
+    table: RwArc<FileTable>,
+    /// The [`FdArray`] of `table`, which is cached so that it can be accessed without locking
+    /// `table`.
+    fd_array: Arc<FdArray>,
+}
+
+impl LocalFileTable {
+    fn new(table: RwArc<FileTable>) -> Self {
+        let fd_array = table.read().fd_array().clone();
+        Self { table, fd_array }
+    }
 }
 
 /// An immutable, shared reference to the file table in [`ThreadLocal`].
-pub struct FileTableRef<'a>(Ref<'a, Option<RwArc<FileTable>>>);
+pub struct FileTableRef<'a>(Ref<'a, Option<LocalFileTable>>);
 
 impl FileTableRef<'_> {
     /// Unwraps and returns a reference to the file table.
@@ -95,21 +134,38 @@ impl FileTableRef<'_> {
     ///
     /// This method will panic if the thread has exited and the file table has been dropped.
     pub fn unwrap(&self) -> &RwArc<FileTable> {
-        self.0.as_ref().unwrap()
+        &self.0.as_ref().unwrap().table
     }
 }
 
 /// A mutable, exclusive reference to the file table in [`ThreadLocal`].
-pub struct FileTableRefMut<'a>(RefMut<'a, Option<RwArc<FileTable>>>);
+pub struct FileTableRefMut<'a>(RefMut<'a, Option<LocalFileTable>>);
 
 impl FileTableRefMut<'_> {
     /// Unwraps and returns a reference to the file table.
     ///
+    /// The file table should be replaced by [`Self::replace`] instead of via the returned
+    /// reference.
+    ///
     /// # Panics
     ///
     /// This method will panic if the thread has exited and the file table has been dropped.
     pub fn unwrap(&mut self) -> &mut RwArc<FileTable> {
-        self.0.as_mut().unwrap()
+        &mut self.0.as_mut().unwrap().table
+    }
+
+    /// Unwraps and returns the [`FdArray`] of the file table.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the thread has exited and the file table has been dropped.
+    pub fn fd_array(&self) -> &FdArray {
+        &self.0.as_ref().unwrap().fd_array
+    }
+
+    /// Replaces the file table with a new one.
+    pub fn replace(&mut self, file_table: RwArc<FileTable>) {
+        *self.0 = Some(LocalFileTable::new(file_table));
     }
 
     /// Removes the file table and drops it.
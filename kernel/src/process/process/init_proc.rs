@@ -12,7 +12,7 @@ use crate::{
     },
     prelude::*,
     process::{
-        posix_thread::{allocate_posix_tid, PosixThreadBuilder, ThreadName},
+        posix_thread::{allocate_posix_tid, AsPosixThread, PosixThreadBuilder, ThreadName},
         process_table,
         process_vm::ProcessVm,
         rlimit::ResourceLimits,
@@ -76,6 +76,13 @@ fn create_init_process(
     )?;
     init_proc.tasks().lock().insert(init_task).unwrap();
 
+    let credentials = init_proc
+        .main_thread()
+        .as_posix_thread()
+        .unwrap()
+        .credentials();
+    init_proc.update_vdso_identity(&credentials);
+
     Ok(init_proc)
 }
 
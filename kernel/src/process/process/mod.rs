@@ -2,6 +2,8 @@
 
 use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
+use aster_rights::ReadOp;
+
 use self::timer_manager::PosixTimerManager;
 use super::{
     posix_thread::AsPosixThread,
@@ -15,10 +17,12 @@ use super::{
     },
     status::ProcessStatus,
     task_set::TaskSet,
+    Credentials,
 };
 use crate::{
     prelude::*,
     sched::{AtomicNice, Nice},
+    syscall::audit::SyscallSet,
     thread::{AsThread, Thread},
     time::clocks::ProfClock,
 };
@@ -37,6 +41,7 @@ use ostd::{sync::WaitQueue, task::Task};
 pub use process_group::ProcessGroup;
 pub use session::Session;
 pub use terminal::Terminal;
+pub use timer_manager::PosixTimer;
 
 /// Process id.
 pub type Pid = u32;
@@ -108,11 +113,17 @@ pub struct Process {
     /// The signal that should be sent to the parent when this process exits.
     exit_signal: AtomicSigNum,
 
+    /// Whether the process can be dumped, or have its memory read by other processes.
+    is_dumpable: AtomicBool,
+
     /// A profiling clock measures the user CPU time and kernel CPU time of the current process.
     prof_clock: Arc<ProfClock>,
 
     /// A manager that manages timer resources and utilities of the process.
     timer_manager: PosixTimerManager,
+
+    /// The syscalls denied for the process.
+    syscall_deny_list: SyscallSet,
 }
 
 /// Representing a parent process by holding a weak reference to it and its PID.
@@ -213,10 +224,12 @@ impl Process {
             sig_dispositions,
             parent_death_signal: AtomicSigNum::new_empty(),
             exit_signal: AtomicSigNum::new_empty(),
+            is_dumpable: AtomicBool::new(true),
             resource_limits,
             nice: AtomicNice::new(nice),
             timer_manager: PosixTimerManager::new(&prof_clock, process_ref),
             prof_clock,
+            syscall_deny_list: SyscallSet::new(),
         })
     }
 
@@ -269,6 +282,13 @@ impl Process {
         &self.nice
     }
 
+    /// Returns the syscalls denied for the process.
+    ///
+    /// The list is set by a privileged supervisor via `/proc/[pid]/syscall_deny`.
+    pub fn syscall_deny_list(&self) -> &SyscallSet {
+        &self.syscall_deny_list
+    }
+
     pub fn main_thread(&self) -> Arc<Thread> {
         self.tasks.lock().main().as_thread().unwrap().clone()
     }
@@ -594,6 +614,17 @@ impl Process {
         self.process_vm.init_stack_reader()
     }
 
+    /// Publishes the identity of the process in the VDSO, with the user and
+    /// group IDs taken from `credentials`.
+    ///
+    /// This should be called whenever the process gets a new address space or
+    /// changes its user or group IDs.
+    pub fn update_vdso_identity(&self, credentials: &Credentials<ReadOp>) {
+        self.process_vm
+            .vdso_identity()
+            .update(self.pid, credentials);
+    }
+
     // ****************** Signal ******************
 
     pub fn sig_dispositions(&self) -> &Arc<Mutex<SigDispositions>> {
@@ -608,7 +639,7 @@ impl Process {
     /// chooses an arbitrary thread to which to deliver the signal.
     ///
     /// TODO: restrict these method with access control tool.
-    pub fn enqueue_signal(&self, signal: impl Signal + Clone + 'static) {
+    pub fn enqueue_signal(&self, signal: impl Signal + 'static) {
         if self.status.is_zombie() {
             return;
         }
@@ -657,6 +688,21 @@ impl Process {
         self.exit_signal.as_sig_num()
     }
 
+    // ******************* Dumpable ********************
+
+    /// Sets whether the process can be dumped.
+    ///
+    /// A non-dumpable process produces no core dumps, and its memory cannot be
+    /// read by other processes through `/proc/[pid]/mem`.
+    pub fn set_dumpable(&self, is_dumpable: bool) {
+        self.is_dumpable.store(is_dumpable, Ordering::Relaxed);
+    }
+
+    /// Returns whether the process can be dumped.
+    pub fn is_dumpable(&self) -> bool {
+        self.is_dumpable.load(Ordering::Relaxed)
+    }
+
     // ******************* Status ********************
 
     /// Returns a reference to the process status.
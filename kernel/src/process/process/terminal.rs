@@ -6,8 +6,8 @@ use super::{session::SessionGuard, JobControl, Pgid, Process, Session, Sid};
 use crate::{
     current_userspace,
     fs::{inode_handle::FileIo, utils::IoctlCmd},
-    prelude::{current, return_errno_with_message, warn, Errno, Error, Result},
-    process::process_table,
+    prelude::{current, current_thread, return_errno_with_message, Errno, Error, Result},
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread, process_table},
 };
 
 /// A terminal.
@@ -49,11 +49,12 @@ impl dyn Terminal {
 
             // Commands about sessions
             IoctlCmd::TIOCSCTTY => {
+                let current = current!();
                 if arg == 1 {
-                    warn!("stealing TTY from another session is not supported");
+                    self.steal_control(&current)?;
                 }
 
-                self.set_control(&current!())
+                self.set_control(&current)
             }
             IoctlCmd::TIOCNOTTY => {
                 if via_master {
@@ -85,7 +86,7 @@ impl dyn Terminal {
 
             // Commands that are invalid or not supported
             _ => {
-                return_errno_with_message!(Errno::EINVAL, "the `ioctl` command is invalid")
+                return_errno_with_message!(Errno::ENOTTY, "the `ioctl` command is unknown")
             }
         }
     }
@@ -123,6 +124,85 @@ impl dyn Terminal {
         Ok(())
     }
 
+    /// Releases the terminal from the session that it controls, if the session is not the
+    /// session of the process.
+    ///
+    /// This allows the terminal to be stolen by the session of the process. Stealing a terminal
+    /// requires `CAP_SYS_ADMIN`.
+    fn steal_control(self: &Arc<Self>, process: &Process) -> Result<()> {
+        let Some(session) = self.job_control().session() else {
+            return Ok(());
+        };
+
+        let process_session = process
+            .process_group
+            .lock()
+            .upgrade()
+            .unwrap()
+            .session()
+            .unwrap();
+        if Arc::ptr_eq(&process_session, &session) || !process_session.is_leader(process) {
+            // `set_control` will succeed or fail without stealing the terminal.
+            return Ok(());
+        }
+
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "stealing a terminal from another session requires CAP_SYS_ADMIN"
+            );
+        }
+
+        // Lock order: session inner -> job control
+        let mut session_inner = session.lock();
+        if session_inner
+            .terminal()
+            .is_some_and(|session_terminal| Arc::ptr_eq(session_terminal, self))
+        {
+            session_inner.set_terminal(None);
+        }
+        self.job_control().unset_session();
+
+        Ok(())
+    }
+
+    /// Hangs up the terminal.
+    ///
+    /// The terminal is released from the session that it controls, if any. `SIGHUP` and `SIGCONT`
+    /// are sent to the session leader and the foreground process group.
+    pub fn hang_up(self: &Arc<Self>) {
+        use crate::process::signal::{
+            constants::{SIGCONT, SIGHUP},
+            signals::kernel::KernelSignal,
+        };
+
+        let Some(session) = self.job_control().session() else {
+            return;
+        };
+
+        // Lock order: session inner -> job control
+        let foreground = {
+            let mut session_inner = session.lock();
+            if session_inner
+                .terminal()
+                .is_some_and(|session_terminal| Arc::ptr_eq(session_terminal, self))
+            {
+                session_inner.set_terminal(None);
+            }
+            self.job_control().unset_session()
+        };
+
+        if let Some(leader) = process_table::get_process(session.sid()) {
+            leader.enqueue_signal(KernelSignal::new(SIGHUP));
+            leader.enqueue_signal(KernelSignal::new(SIGCONT));
+        }
+        if let Some(foreground) = foreground {
+            foreground.broadcast_signal(KernelSignal::new(SIGHUP));
+            foreground.broadcast_signal(KernelSignal::new(SIGCONT));
+        }
+    }
+
     /// Unsets the terminal from the controlling terminal of the process.
     fn unset_control(self: Arc<Self>, process: &Process) -> Result<()> {
         // Lock order: group of process -> session inner -> job control
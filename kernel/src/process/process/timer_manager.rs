@@ -1,24 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{
-    boxed::Box,
-    sync::{Arc, Weak},
-    vec::Vec,
-};
 use core::time::Duration;
 
 use id_alloc::IdAlloc;
 use ostd::{
     arch::{timer::TIMER_FREQ, trap::is_kernel_interrupted},
-    sync::Mutex,
     timer,
 };
 
 use super::Process;
 use crate::{
+    prelude::*,
     process::{
         posix_thread::AsPosixThread,
-        signal::{constants::SIGALRM, signals::kernel::KernelSignal},
+        signal::{
+            constants::SIGALRM,
+            signals::{kernel::KernelSignal, timer::TimerOverrun},
+        },
     },
     thread::{
         work_queue::{submit_work_item, work_item::WorkItem},
@@ -97,7 +95,29 @@ pub struct PosixTimerManager {
     id_allocator: Mutex<IdAlloc>,
     /// A container managing all POSIX timers created by `timer_create()` syscall
     /// within the process context.
-    posix_timers: Mutex<Vec<Option<Arc<Timer>>>>,
+    posix_timers: Mutex<Vec<Option<Arc<PosixTimer>>>>,
+}
+
+/// A POSIX timer created by `timer_create()` syscall.
+pub struct PosixTimer {
+    timer: Arc<Timer>,
+    overrun: Arc<TimerOverrun>,
+}
+
+impl PosixTimer {
+    pub fn new(timer: Arc<Timer>, overrun: Arc<TimerOverrun>) -> Self {
+        Self { timer, overrun }
+    }
+
+    /// Gets the underlying timer.
+    pub fn timer(&self) -> &Arc<Timer> {
+        &self.timer
+    }
+
+    /// Gets the overrun counter of the timer.
+    pub fn overrun(&self) -> &TimerOverrun {
+        &self.overrun
+    }
 }
 
 fn create_process_timer_callback(process_ref: &Weak<Process>) -> impl Fn() + Clone {
@@ -172,23 +192,39 @@ impl PosixTimerManager {
         self.virtual_timer.timer_manager().create_timer(func)
     }
 
-    /// Adds a POSIX timer to the managed `posix_timers`, and allocate a timer ID for this timer.
-    /// Return the timer ID.
-    pub fn add_posix_timer(&self, posix_timer: Arc<Timer>) -> usize {
+    /// Allocates a timer ID, and adds the POSIX timer created by `new_timer` with this ID to the
+    /// managed `posix_timers`.
+    ///
+    /// Returns the timer ID.
+    pub fn add_posix_timer<F>(&self, new_timer: F) -> Result<usize>
+    where
+        F: FnOnce(usize) -> Result<PosixTimer>,
+    {
         let mut timers = self.posix_timers.lock();
         // Holding the lock of `posix_timers` is required to operate the `id_allocator`.
-        let timer_id = self.id_allocator.lock().alloc().unwrap();
+        let mut id_allocator = self.id_allocator.lock();
+        let Some(timer_id) = id_allocator.alloc() else {
+            return_errno_with_message!(Errno::EAGAIN, "too many POSIX timers");
+        };
+        let posix_timer = match new_timer(timer_id) {
+            Ok(posix_timer) => posix_timer,
+            Err(err) => {
+                id_allocator.free(timer_id);
+                return Err(err);
+            }
+        };
+
         if timers.len() < timer_id + 1 {
             timers.resize(timer_id + 1, None);
         }
         // The ID allocated is not used by any other timers so this index in `timers`
         // must be `None`.
-        timers[timer_id] = Some(posix_timer);
-        timer_id
+        timers[timer_id] = Some(Arc::new(posix_timer));
+        Ok(timer_id)
     }
 
     /// Finds a POSIX timer by the input `timer_id`.
-    pub fn find_posix_timer(&self, timer_id: usize) -> Option<Arc<Timer>> {
+    pub fn find_posix_timer(&self, timer_id: usize) -> Option<Arc<PosixTimer>> {
         let timers = self.posix_timers.lock();
         if timer_id >= timers.len() {
             return None;
@@ -198,7 +234,7 @@ impl PosixTimerManager {
     }
 
     /// Removes the POSIX timer with the ID `timer_id`.
-    pub fn remove_posix_timer(&self, timer_id: usize) -> Option<Arc<Timer>> {
+    pub fn remove_posix_timer(&self, timer_id: usize) -> Option<Arc<PosixTimer>> {
         let mut timers = self.posix_timers.lock();
         if timer_id >= timers.len() {
             return None;
@@ -8,13 +8,15 @@
 
 use alloc::collections::btree_map::Values;
 
+use ostd::sync::{PiMutex, PiMutexGuard};
+
 use super::{Pgid, Pid, Process, ProcessGroup, Session, Sid};
 use crate::{
     events::{Events, Observer, Subject},
     prelude::*,
 };
 
-static PROCESS_TABLE: Mutex<ProcessTable> = Mutex::new(ProcessTable::new());
+static PROCESS_TABLE: PiMutex<ProcessTable> = PiMutex::new(ProcessTable::new());
 static PROCESS_GROUP_TABLE: Mutex<BTreeMap<Pgid, Arc<ProcessGroup>>> = Mutex::new(BTreeMap::new());
 static SESSION_TABLE: Mutex<BTreeMap<Sid, Arc<Session>>> = Mutex::new(BTreeMap::new());
 
@@ -24,7 +26,7 @@ pub fn get_process(pid: Pid) -> Option<Arc<Process>> {
     PROCESS_TABLE.lock().get(pid).cloned()
 }
 
-pub fn process_table_mut() -> MutexGuard<'static, ProcessTable> {
+pub fn process_table_mut() -> PiMutexGuard<'static, ProcessTable> {
     PROCESS_TABLE.lock()
 }
 
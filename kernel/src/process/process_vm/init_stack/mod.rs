@@ -29,7 +29,7 @@ use crate::{
     util::random::getrandom,
     vm::{
         perms::VmPerms,
-        vmar::Vmar,
+        vmar::{vm_mapping::MappingName, Vmar},
         vmo::{Vmo, VmoOptions, VmoRightsOp},
     },
 };
@@ -130,7 +130,7 @@ impl InitStack {
             // make the stack values of a buggy user program harder
             // to be exploited by attackers.
             let mut nr_random_padding_pages: u8 = 0;
-            getrandom(nr_random_padding_pages.as_bytes_mut()).unwrap();
+            getrandom(nr_random_padding_pages.as_bytes_mut());
 
             nr_random_padding_pages as usize + NR_FIXED_PADDING_PAGES
         };
@@ -176,6 +176,7 @@ impl InitStack {
                 .new_map(self.max_size, perms)?
                 .offset(map_addr)
                 .vmo(vmo.dup().to_dyn())
+                .name(MappingName::Stack)
         };
         vmar_map_options.build()?;
 
@@ -367,7 +368,7 @@ impl InitStackWriter {
 
 fn generate_random_for_aux_vec() -> [u8; 16] {
     let mut rand_val = [0; 16];
-    getrandom(&mut rand_val).unwrap();
+    getrandom(&mut rand_val);
     rand_val
 }
 
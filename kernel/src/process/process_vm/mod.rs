@@ -12,6 +12,8 @@
 mod heap;
 mod init_stack;
 
+use core::sync::atomic::{AtomicU32, Ordering};
+
 use aster_rights::Full;
 pub use heap::Heap;
 use ostd::{sync::MutexGuard, task::disable_preempt};
@@ -24,7 +26,7 @@ pub use self::{
         MAX_ENV_LEN,
     },
 };
-use crate::{prelude::*, vm::vmar::Vmar};
+use crate::{prelude::*, vdso::VdsoIdentity, vm::vmar::Vmar};
 
 /*
  * The user's virtual memory space layout looks like below.
@@ -67,6 +69,9 @@ pub struct ProcessVm {
     root_vmar: Mutex<Option<Vmar<Full>>>,
     init_stack: InitStack,
     heap: Heap,
+    vdso_identity: Arc<VdsoIdentity>,
+    /// The `membarrier` commands that the process has registered for.
+    membarrier_registrations: AtomicU32,
 }
 
 /// A guard to the [`Vmar`] used by a process.
@@ -87,6 +92,13 @@ impl ProcessVmarGuard<'_> {
         self.inner.as_ref().unwrap()
     }
 
+    /// Returns a reference to the process VMAR if it exists.
+    ///
+    /// Returns `None` if the process has exited and its VMAR has been dropped.
+    pub fn as_ref(&self) -> Option<&Vmar<Full>> {
+        self.inner.as_ref()
+    }
+
     /// Sets a new VMAR for the binding process.
     ///
     /// If the `new_vmar` is `None`, this method will remove the
@@ -99,10 +111,13 @@ impl ProcessVmarGuard<'_> {
 impl Clone for ProcessVm {
     fn clone(&self) -> Self {
         let root_vmar = self.lock_root_vmar();
+        // The identity page cannot describe both processes sharing the address space.
+        self.vdso_identity.set_shared();
         Self {
             root_vmar: Mutex::new(Some(root_vmar.unwrap().dup().unwrap())),
             init_stack: self.init_stack.clone(),
             heap: self.heap.clone(),
+            vdso_identity: self.vdso_identity.clone(),
         }
     }
 }
@@ -118,6 +133,8 @@ impl ProcessVm {
             root_vmar: Mutex::new(Some(root_vmar)),
             heap,
             init_stack,
+            vdso_identity: Arc::new(VdsoIdentity::new().unwrap()),
+            membarrier_registrations: AtomicU32::new(0),
         }
     }
 
@@ -126,11 +143,22 @@ impl ProcessVm {
     /// The returned `ProcessVm` will have a forked `Vmar`.
     pub fn fork_from(other: &ProcessVm) -> Result<Self> {
         let process_vmar = other.lock_root_vmar();
-        let root_vmar = Mutex::new(Some(Vmar::<Full>::fork_from(process_vmar.unwrap())?));
+        let root_vmar = Vmar::<Full>::fork_from(process_vmar.unwrap())?;
+
+        // The forked identity page belongs to `other`, so replace it with a new one.
+        let vdso_identity = VdsoIdentity::new()?;
+        if let Some(addr) = other.vdso_identity.map_addr() {
+            vdso_identity.map_to(&root_vmar, addr)?;
+        }
+
         Ok(Self {
-            root_vmar,
+            root_vmar: Mutex::new(Some(root_vmar)),
             heap: other.heap.clone(),
             init_stack: other.init_stack.clone(),
+            vdso_identity: Arc::new(vdso_identity),
+            membarrier_registrations: AtomicU32::new(
+                other.membarrier_registrations.load(Ordering::Relaxed),
+            ),
         })
     }
 
@@ -147,11 +175,27 @@ impl ProcessVm {
         self.init_stack.reader(self.lock_root_vmar())
     }
 
+    /// Returns the identity page in the VDSO.
+    pub fn vdso_identity(&self) -> &VdsoIdentity {
+        &self.vdso_identity
+    }
+
     /// Returns the top address of the user stack.
     pub fn user_stack_top(&self) -> Vaddr {
         self.init_stack.user_stack_top()
     }
 
+    /// Returns the `membarrier` commands that the process has registered for.
+    pub fn membarrier_registrations(&self) -> u32 {
+        self.membarrier_registrations.load(Ordering::Relaxed)
+    }
+
+    /// Registers the process for the `membarrier` commands.
+    pub fn register_membarrier(&self, cmds: u32) {
+        self.membarrier_registrations
+            .fetch_or(cmds, Ordering::Relaxed);
+    }
+
     pub(super) fn map_and_write_init_stack(
         &self,
         argv: Vec<CString>,
@@ -172,6 +216,8 @@ impl ProcessVm {
         let root_vmar = self.lock_root_vmar();
         root_vmar.unwrap().clear().unwrap();
         self.heap.alloc_and_map_vm(&root_vmar.unwrap()).unwrap();
+        // The registrations do not survive `execve`.
+        self.membarrier_registrations.store(0, Ordering::Relaxed);
     }
 }
 
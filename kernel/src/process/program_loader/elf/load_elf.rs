@@ -22,11 +22,11 @@ use crate::{
         process_vm::{AuxKey, AuxVec, ProcessVm},
         TermStatus,
     },
-    vdso::{vdso_vmo, VDSO_VMO_SIZE},
+    vdso::{vdso_vmo, VDSO_IDENTITY_OFFSET, VDSO_TEXT_OFFSET},
     vm::{
         perms::VmPerms,
         util::duplicate_frame,
-        vmar::Vmar,
+        vmar::{vm_mapping::MappingName, Vmar},
         vmo::{CommitFlags, VmoRightsOp},
     },
 };
@@ -301,7 +301,8 @@ fn map_segment_vmo(
             .vmo(segment_vmo.dup()?)
             .vmo_offset(segment_offset)
             .vmo_limit(segment_offset + segment_size)
-            .can_overwrite(true);
+            .can_overwrite(true)
+            .name(MappingName::File(elf_file.clone()));
         vm_map_options = vm_map_options.offset(offset).handle_page_faults_around();
         let map_addr = vm_map_options.build()?;
 
@@ -429,15 +430,19 @@ pub fn init_aux_vec(elf: &Elf, elf_map_addr: Vaddr, ldso_base: Option<Vaddr>) ->
 fn map_vdso_to_vm(process_vm: &ProcessVm) -> Option<Vaddr> {
     let process_vmar = process_vm.lock_root_vmar();
     let root_vmar = process_vmar.unwrap();
+    // The old mapping of the identity page has gone with the old program.
+    process_vm.vdso_identity().clear_map_addr();
     let vdso_vmo = vdso_vmo()?;
+    let vdso_size = vdso_vmo.size();
 
     let options = root_vmar
-        .new_map(VDSO_VMO_SIZE, VmPerms::empty())
+        .new_map(vdso_size, VmPerms::empty())
         .unwrap()
-        .vmo(vdso_vmo.dup().unwrap());
+        .vmo(vdso_vmo.dup().unwrap())
+        .name(MappingName::Vdso);
 
     let vdso_data_base = options.build().unwrap();
-    let vdso_text_base = vdso_data_base + 0x4000;
+    let vdso_text_base = vdso_data_base + VDSO_TEXT_OFFSET;
 
     let data_perms = VmPerms::READ | VmPerms::WRITE;
     let text_perms = VmPerms::READ | VmPerms::EXEC;
@@ -445,7 +450,11 @@ fn map_vdso_to_vm(process_vm: &ProcessVm) -> Option<Vaddr> {
         .protect(data_perms, vdso_data_base..vdso_data_base + PAGE_SIZE)
         .unwrap();
     root_vmar
-        .protect(text_perms, vdso_text_base..vdso_text_base + PAGE_SIZE)
+        .protect(text_perms, vdso_text_base..vdso_data_base + vdso_size)
+        .unwrap();
+    process_vm
+        .vdso_identity()
+        .map_to(root_vmar, vdso_data_base + VDSO_IDENTITY_OFFSET)
         .unwrap();
     Some(vdso_text_base)
 }
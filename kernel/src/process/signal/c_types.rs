@@ -57,6 +57,42 @@ impl siginfo_t {
     pub fn si_addr(&self) -> Vaddr {
         read_union_field!(self, Self, siginfo_fields.sigfault.addr)
     }
+
+    pub fn set_si_pid(&mut self, si_pid: Pid) {
+        self.siginfo_fields.common.first.piduid.pid = si_pid;
+    }
+
+    pub fn si_pid(&self) -> Pid {
+        read_union_field!(self, Self, siginfo_fields.common.first.piduid.pid)
+    }
+
+    pub fn set_si_uid(&mut self, si_uid: Uid) {
+        self.siginfo_fields.common.first.piduid.uid = si_uid;
+    }
+
+    pub fn si_uid(&self) -> Uid {
+        read_union_field!(self, Self, siginfo_fields.common.first.piduid.uid)
+    }
+
+    pub fn set_si_timer(&mut self, timer_id: i32, overrun: i32, value: sigval_t) {
+        self.siginfo_fields.common.first.timer = siginfo_timer_t {
+            timerid: timer_id,
+            overrun,
+        };
+        self.siginfo_fields.common.second.value = value;
+    }
+
+    pub fn si_timerid(&self) -> i32 {
+        read_union_field!(self, Self, siginfo_fields.common.first.timer.timerid)
+    }
+
+    pub fn si_overrun(&self) -> i32 {
+        read_union_field!(self, Self, siginfo_fields.common.first.timer.overrun)
+    }
+
+    pub fn si_value(&self) -> sigval_t {
+        read_union_field!(self, Self, siginfo_fields.common.second.value)
+    }
 }
 
 #[derive(Clone, Copy, Pod)]
@@ -122,6 +158,10 @@ impl sigval_t {
         read_union_field!(self, Self, sigval_int)
     }
 
+    pub fn set_int(&mut self, value: i32) {
+        self.sigval_int = value;
+    }
+
     pub fn read_ptr(&self) -> Vaddr {
         read_union_field!(self, Self, sigval_ptr)
     }
@@ -7,22 +7,24 @@ mod pause;
 mod poll;
 pub mod sig_action;
 pub mod sig_disposition;
+mod sig_frame;
 pub mod sig_mask;
 pub mod sig_num;
 pub mod sig_queues;
 mod sig_stack;
 pub mod signals;
 
-use core::{mem, sync::atomic::Ordering};
+use core::sync::atomic::Ordering;
 
 use align_ext::AlignExt;
 use c_types::{siginfo_t, ucontext_t};
 use constants::SIGSEGV;
 pub use events::{SigEvents, SigEventsFilter};
 use ostd::{cpu::context::UserContext, user::UserContextApi};
-pub use pause::{with_sigmask_changed, Pause};
+pub use pause::{with_sigmask_changed, with_sigmask_replaced, Pause};
 pub use poll::{PollAdaptor, PollHandle, Pollable, Pollee, Poller};
 use sig_action::{SigAction, SigActionFlags, SigDefaultAction};
+pub use sig_frame::{SigFpstateLayout, SigFrameLayout};
 use sig_mask::SigMask;
 use sig_num::SigNum;
 pub use sig_stack::{SigStack, SigStackFlags};
@@ -30,7 +32,6 @@ pub use sig_stack::{SigStack, SigStackFlags};
 use super::posix_thread::ThreadLocal;
 use crate::{
     cpu::LinuxAbi,
-    current_userspace,
     prelude::*,
     process::{posix_thread::do_exit_group, TermStatus},
 };
@@ -38,6 +39,15 @@ use crate::{
 pub trait SignalContext {
     /// Set signal handler arguments
     fn set_arguments(&mut self, sig_num: SigNum, siginfo_addr: usize, ucontext_addr: usize);
+
+    /// Returns the layout of the FPU state in the signal frames.
+    fn fpstate_layout(&self) -> SigFpstateLayout;
+
+    /// Saves the current FPU state to the signal frame at `addr`.
+    fn save_fpstate(&self, addr: Vaddr, user_space: &CurrentUserSpace) -> Result<()>;
+
+    /// Restores the FPU state from the signal frame at `addr`.
+    fn restore_fpstate(&mut self, addr: Vaddr, user_space: &CurrentUserSpace) -> Result<()>;
 }
 
 // TODO: This interface of this method is error prone.
@@ -49,11 +59,13 @@ pub fn handle_pending_signal(
     syscall_number: Option<usize>,
 ) {
     let syscall_restart = if let Some(syscall_number) = syscall_number
-        && user_ctx.syscall_ret() == -(Errno::ERESTARTSYS as i32) as usize
+        && let Some(errno) = [Errno::ERESTARTSYS, Errno::ERESTARTNOHAND]
+            .into_iter()
+            .find(|errno| user_ctx.syscall_ret() == -(*errno as i32) as usize)
     {
-        // We should never return `ERESTARTSYS` to the userspace.
+        // We should never return `ERESTARTSYS` or `ERESTARTNOHAND` to the userspace.
         user_ctx.set_syscall_ret(-(Errno::EINTR as i32) as usize);
-        Some(syscall_number)
+        Some((syscall_number, errno))
     } else {
         None
     };
@@ -66,6 +78,11 @@ pub fn handle_pending_signal(
         if let Some(signal) = posix_thread.dequeue_signal(&sig_mask) {
             signal
         } else {
+            // Like Linux, the interrupted syscall is restarted if no signal handler is invoked.
+            if let Some((syscall_number, _)) = syscall_restart {
+                restart_syscall(user_ctx, syscall_number);
+            }
+            restore_saved_sig_mask(ctx);
             return;
         }
     };
@@ -77,6 +94,11 @@ pub fn handle_pending_signal(
     let sig_action = sig_dispositions.get(sig_num);
     trace!("sig action: {:x?}", sig_action);
 
+    let is_handler_invoked = matches!(sig_action, SigAction::User { .. });
+    if !is_handler_invoked && let Some((syscall_number, _)) = syscall_restart {
+        restart_syscall(user_ctx, syscall_number);
+    }
+
     match sig_action {
         SigAction::Ign => {
             trace!("Ignore signal {:?}", sig_num);
@@ -87,17 +109,10 @@ pub fn handle_pending_signal(
             restorer_addr,
             mask,
         } => {
-            if let Some(syscall_number) = syscall_restart
+            if let Some((syscall_number, Errno::ERESTARTSYS)) = syscall_restart
                 && flags.contains(SigActionFlags::SA_RESTART)
             {
-                #[cfg(target_arch = "x86_64")]
-                const SYSCALL_INSTR_LEN: usize = 2; // syscall
-                #[cfg(target_arch = "riscv64")]
-                const SYSCALL_INSTR_LEN: usize = 4; // ecall
-
-                user_ctx.set_syscall_num(syscall_number);
-                user_ctx
-                    .set_instruction_pointer(user_ctx.instruction_pointer() - SYSCALL_INSTR_LEN);
+                restart_syscall(user_ctx, syscall_number);
             }
 
             if flags.contains(SigActionFlags::SA_RESETHAND) {
@@ -149,6 +164,29 @@ pub fn handle_pending_signal(
             }
         }
     }
+
+    restore_saved_sig_mask(ctx);
+}
+
+/// Makes the syscall be executed again when returning to the user space.
+fn restart_syscall(user_ctx: &mut UserContext, syscall_number: usize) {
+    #[cfg(target_arch = "x86_64")]
+    const SYSCALL_INSTR_LEN: usize = 2; // syscall
+    #[cfg(target_arch = "riscv64")]
+    const SYSCALL_INSTR_LEN: usize = 4; // ecall
+
+    user_ctx.set_syscall_num(syscall_number);
+    user_ctx.set_instruction_pointer(user_ctx.instruction_pointer() - SYSCALL_INSTR_LEN);
+}
+
+/// Restores the signal mask saved by [`with_sigmask_replaced`], if no signal handler has taken
+/// it over.
+fn restore_saved_sig_mask(ctx: &Context) {
+    if let Some(saved_mask) = ctx.thread_local.saved_sig_mask().take() {
+        ctx.posix_thread
+            .sig_mask()
+            .store(saved_mask, Ordering::Relaxed);
+    }
 }
 
 #[expect(clippy::too_many_arguments)]
@@ -181,29 +219,34 @@ pub fn handle_user_signal(
     ctx.posix_thread
         .sig_mask()
         .store(old_mask + mask, Ordering::Relaxed);
+    // The signal mask to restore when the handler returns. If the signal mask is replaced
+    // temporarily by the interrupted syscall, the original one is restored instead.
+    let restored_mask = ctx.thread_local.saved_sig_mask().take().unwrap_or(old_mask);
 
     // Set up signal stack.
-    let mut stack_pointer = if let Some(sp) = use_alternate_signal_stack(ctx.thread_local) {
-        sp as u64
+    let stack_pointer = if let Some(sp) = use_alternate_signal_stack(ctx.thread_local) {
+        sp
     } else {
         // Just use user stack
-        user_ctx.stack_pointer() as u64
+        user_ctx.stack_pointer()
     };
-
-    // To avoid corrupting signal stack, we minus 128 first.
-    stack_pointer -= 128;
+    let frame = sig_frame_layout(ctx.thread_local, user_ctx)
+        .place(stack_pointer, flags.contains(SigActionFlags::SA_RESTORER));
 
     let user_space = ctx.user_space();
 
-    // 1. Write siginfo_t
-    stack_pointer -= mem::size_of::<siginfo_t>() as u64;
-    user_space.write_val(stack_pointer as _, &sig_info)?;
-    let siginfo_addr = stack_pointer;
+    // 1. Write the FPU state.
+    if let Some(fpstate_addr) = frame.fpstate_addr {
+        user_ctx.save_fpstate(fpstate_addr, &user_space)?;
+    }
+
+    // 2. Write siginfo_t
+    user_space.write_val(frame.siginfo_addr, &sig_info)?;
 
-    // 2. Write ucontext_t.
-    stack_pointer = alloc_aligned_in_user_stack(stack_pointer, mem::size_of::<ucontext_t>(), 16)?;
+    // 3. Write ucontext_t.
     let mut ucontext = ucontext_t {
-        uc_sigmask: mask.into(),
+        uc_sigmask: restored_mask.into(),
+        uc_link: ctx.thread_local.sig_context().get().unwrap_or(0),
         ..Default::default()
     };
     ucontext
@@ -211,36 +254,25 @@ pub fn handle_user_signal(
         .inner
         .gp_regs
         .copy_from_raw(user_ctx.general_regs());
-    let sig_context = ctx.thread_local.sig_context().get();
-    if let Some(sig_context_addr) = sig_context {
-        ucontext.uc_link = sig_context_addr;
-    } else {
-        ucontext.uc_link = 0;
-    }
-    // TODO: store fp regs in ucontext
-    user_space.write_val(stack_pointer as _, &ucontext)?;
-    let ucontext_addr = stack_pointer;
+    ucontext.uc_mcontext.inner.fpregs = frame.fpstate_addr.unwrap_or(0);
+    user_space.write_val(frame.ucontext_addr, &ucontext)?;
     // Store the ucontext addr in sig context of current thread.
     ctx.thread_local
         .sig_context()
-        .set(Some(ucontext_addr as Vaddr));
+        .set(Some(frame.ucontext_addr));
 
-    // 3. Write the address of the restorer code.
+    // 4. Write the address of the restorer code.
     if flags.contains(SigActionFlags::SA_RESTORER) {
         // If the SA_RESTORER flag is present, the restorer code address is provided by the user.
-        stack_pointer = write_u64_to_user_stack(stack_pointer, restorer_addr as u64)?;
-        trace!(
-            "After writing restorer addr: user_rsp = 0x{:x}",
-            stack_pointer
-        );
+        user_space.write_val(frame.stack_pointer, &(restorer_addr as u64))?;
     }
 
-    // 4. Set correct register values
+    // 5. Set correct register values
     user_ctx.set_instruction_pointer(handler_addr as _);
-    user_ctx.set_stack_pointer(stack_pointer as usize);
+    user_ctx.set_stack_pointer(frame.stack_pointer);
     // Parameters of signal handler
     if flags.contains(SigActionFlags::SA_SIGINFO) {
-        user_ctx.set_arguments(sig_num, siginfo_addr as usize, ucontext_addr as usize);
+        user_ctx.set_arguments(sig_num, frame.siginfo_addr, frame.ucontext_addr);
     } else {
         user_ctx.set_arguments(sig_num, 0, 0);
     }
@@ -257,6 +289,20 @@ pub fn handle_user_signal(
     Ok(())
 }
 
+/// Returns the layout of the signal frames of the current thread.
+///
+/// The layout is computed when the first signal is delivered and is reused
+/// afterwards, since it does not change during the lifetime of the thread.
+fn sig_frame_layout(thread_local: &ThreadLocal, user_ctx: &UserContext) -> SigFrameLayout {
+    if let Some(layout) = thread_local.sig_frame_layout().get() {
+        return layout;
+    }
+
+    let layout = SigFrameLayout::new(user_ctx);
+    thread_local.sig_frame_layout().set(Some(layout));
+    layout
+}
+
 /// Use an alternate signal stack, which was installed by sigaltstack.
 /// It the stack is already active, we just increase the handler counter and return None, since
 /// the stack pointer can be read from context.
@@ -281,18 +327,3 @@ fn use_alternate_signal_stack(thread_local: &ThreadLocal) -> Option<usize> {
     let stack_pointer = (sig_stack.base() + sig_stack.size()).align_down(16);
     Some(stack_pointer)
 }
-
-fn write_u64_to_user_stack(rsp: u64, value: u64) -> Result<u64> {
-    let rsp = rsp - 8;
-    current_userspace!().write_val(rsp as Vaddr, &value)?;
-    Ok(rsp)
-}
-
-/// alloc memory of size on user stack, the return address should respect the align argument.
-fn alloc_aligned_in_user_stack(rsp: u64, size: usize, align: usize) -> Result<u64> {
-    if !align.is_power_of_two() {
-        return_errno_with_message!(Errno::EINVAL, "align must be power of two");
-    }
-    let start = (rsp - size as u64).align_down(align as u64);
-    Ok(start)
-}
@@ -8,7 +8,7 @@ use super::sig_mask::SigMask;
 use crate::{
     prelude::*,
     process::posix_thread::AsPosixThread,
-    thread::AsThread,
+    thread::{kernel_thread::AsKernelThread, AsThread},
     time::wait::{ManagedTimeout, TimeoutExt},
 };
 
@@ -23,6 +23,10 @@ use crate::{
 /// which are similar to the `wait`-family methods except that the methods also return
 /// when the waiting thread is interrupted by a POSIX signal.
 /// When this happens, the `pause`-family methods return `Err(EINTR)`.
+/// Kernel threads do not receive signals, but they can be interrupted in the same way
+/// (see [`KernelThread::interrupt`]).
+///
+/// [`KernelThread::interrupt`]: crate::thread::kernel_thread::KernelThread::interrupt
 pub trait Pause: WaitTimeout {
     /// Pauses until the condition is met or a signal interrupts.
     ///
@@ -111,11 +115,29 @@ impl Pause for Waiter {
         // No fast paths for `Waiter`. If the caller wants a fast path, it should do so _before_
         // the waiter is created.
 
-        let Some(posix_thread) = self
-            .task()
-            .as_thread()
-            .and_then(|thread| thread.as_posix_thread())
-        else {
+        let Some(thread) = self.task().as_thread() else {
+            return self.wait_until_or_timeout_cancelled(cond, || Ok(()), timeout);
+        };
+
+        if let Some(kernel_thread) = thread.as_kernel_thread() {
+            let cancel_cond = || {
+                if kernel_thread.is_interrupted() {
+                    return Err(Error::with_message(
+                        Errno::EINTR,
+                        "the current kernel thread is interrupted",
+                    ));
+                }
+                Ok(())
+            };
+
+            kernel_thread.set_interrupted_waker(self.waker());
+            let res = self.wait_until_or_timeout_cancelled(cond, cancel_cond, timeout);
+            kernel_thread.clear_interrupted_waker();
+
+            return res;
+        }
+
+        let Some(posix_thread) = thread.as_posix_thread() else {
             return self.wait_until_or_timeout_cancelled(cond, || Ok(()), timeout);
         };
 
@@ -144,15 +166,20 @@ impl Pause for Waiter {
             })
         });
 
-        let posix_thread_opt = self
-            .task()
-            .as_thread()
-            .and_then(|thread| thread.as_posix_thread());
+        let thread_opt = self.task().as_thread();
+        let posix_thread_opt = thread_opt.and_then(|thread| thread.as_posix_thread());
+        let kernel_thread_opt = thread_opt.and_then(|thread| thread.as_kernel_thread());
 
         if let Some(posix_thread) = posix_thread_opt {
             posix_thread.set_signalled_waker(self.waker());
             self.wait();
             posix_thread.clear_signalled_waker();
+        } else if let Some(kernel_thread) = kernel_thread_opt {
+            kernel_thread.set_interrupted_waker(self.waker());
+            if !kernel_thread.is_interrupted() {
+                self.wait();
+            }
+            kernel_thread.clear_interrupted_waker();
         } else {
             self.wait();
         }
@@ -174,6 +201,9 @@ impl Pause for Waiter {
                 "the current thread is interrupted by a signal"
             );
         }
+        if kernel_thread_opt.is_some_and(|kernel_thread| kernel_thread.is_interrupted()) {
+            return_errno_with_message!(Errno::EINTR, "the current kernel thread is interrupted");
+        }
 
         Ok(())
     }
@@ -227,6 +257,40 @@ pub fn with_sigmask_changed<R>(
     res
 }
 
+/// Executes a closure after temporarily replacing the signal mask of the current POSIX thread.
+///
+/// This is for the syscalls that wait for signals with a temporary signal mask atomically (e.g.,
+/// `ppoll`, `pselect6`, and `rt_sigsuspend`). Unlike [`with_sigmask_changed`], if the closure is
+/// interrupted by a signal, the original signal mask is not restored until the signal is
+/// delivered, so that the signals unblocked by the temporary signal mask can be delivered. The
+/// signal handler then restores the original signal mask when it returns.
+pub fn with_sigmask_replaced<R>(
+    ctx: &Context,
+    mask: SigMask,
+    operate: impl FnOnce() -> Result<R>,
+) -> Result<R> {
+    let sig_mask = ctx.posix_thread.sig_mask();
+
+    let old_mask = sig_mask.load(Ordering::Relaxed);
+    sig_mask.store(mask, Ordering::Relaxed);
+
+    let res = operate();
+
+    let is_interrupted = res.as_ref().is_err_and(|err| {
+        matches!(
+            err.error(),
+            Errno::EINTR | Errno::ERESTARTSYS | Errno::ERESTARTNOHAND
+        )
+    });
+    if is_interrupted {
+        ctx.thread_local.saved_sig_mask().set(Some(old_mask));
+    } else {
+        sig_mask.store(old_mask, Ordering::Relaxed);
+    }
+
+    res
+}
+
 #[cfg(ktest)]
 mod test {
     use core::sync::atomic::AtomicBool;
@@ -258,4 +322,27 @@ mod test {
 
         thread.join();
     }
+
+    #[ktest]
+    fn test_kernel_thread_interrupt() {
+        let wait_queue = Arc::new(WaitQueue::new());
+        let wait_queue_cloned = wait_queue.clone();
+
+        let is_interrupted = Arc::new(AtomicBool::new(false));
+        let is_interrupted_cloned = is_interrupted.clone();
+
+        let thread = ThreadOptions::new(move || {
+            let res = wait_queue_cloned.pause_until(|| None::<()>);
+            is_interrupted_cloned.store(
+                res.is_err_and(|err| err.error() == Errno::EINTR),
+                Ordering::Relaxed,
+            );
+        })
+        .spawn();
+
+        thread.as_kernel_thread().unwrap().interrupt();
+        thread.join();
+
+        assert!(is_interrupted.load(Ordering::Relaxed));
+    }
 }
@@ -177,6 +177,14 @@ impl Pollee {
         self.inner.subject.notify_observers(&events);
     }
 
+    /// Returns whether there are any pollers registered to the pollee.
+    ///
+    /// This is only a hint, since the pollers may be registered or unregistered
+    /// concurrently.
+    pub fn has_pollers(&self) -> bool {
+        self.inner.subject.has_observers()
+    }
+
     /// Invalidates the (internal) cached events.
     ///
     /// This method should be called whenever old events disappear but no new events arrive. The
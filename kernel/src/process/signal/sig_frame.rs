@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+use ostd::cpu::context::UserContext;
+
+use super::{
+    c_types::{siginfo_t, ucontext_t},
+    SignalContext,
+};
+use crate::prelude::*;
+
+/// The size of the red zone below the stack pointer, which must not be
+/// corrupted by the signal frames.
+const RED_ZONE_SIZE: usize = 128;
+
+/// The layout of the FPU state in the signal frames.
+#[derive(Debug, Clone, Copy)]
+pub struct SigFpstateLayout {
+    /// The size in bytes, which is zero if the FPU state is not saved.
+    pub size: usize,
+    /// The alignment in bytes.
+    pub align: usize,
+}
+
+/// The layout of the signal frames of a thread.
+///
+/// The signal frame consists of the following parts, from the high address to
+/// the low address:
+///  - The FPU state;
+///  - The `siginfo_t`;
+///  - The `ucontext_t`;
+///  - The address of the restorer code (if the restorer is provided).
+#[derive(Debug, Clone, Copy)]
+pub struct SigFrameLayout {
+    fpstate: SigFpstateLayout,
+}
+
+/// The addresses of a signal frame on the user stack.
+#[derive(Debug)]
+pub(super) struct SigFrame {
+    pub(super) fpstate_addr: Option<Vaddr>,
+    pub(super) siginfo_addr: Vaddr,
+    pub(super) ucontext_addr: Vaddr,
+    /// The stack pointer when the signal handler starts.
+    pub(super) stack_pointer: Vaddr,
+}
+
+impl SigFrameLayout {
+    /// Computes the layout of the signal frames for the user context.
+    pub(super) fn new(user_ctx: &UserContext) -> Self {
+        let fpstate = user_ctx.fpstate_layout();
+        debug_assert!(fpstate.align.is_power_of_two());
+
+        Self { fpstate }
+    }
+
+    /// Places a signal frame below the stack pointer.
+    pub(super) fn place(&self, stack_pointer: Vaddr, has_restorer: bool) -> SigFrame {
+        let mut addr = stack_pointer.wrapping_sub(RED_ZONE_SIZE);
+
+        let fpstate_addr = if self.fpstate.size > 0 {
+            addr = addr
+                .wrapping_sub(self.fpstate.size)
+                .align_down(self.fpstate.align);
+            Some(addr)
+        } else {
+            None
+        };
+
+        addr = addr.wrapping_sub(size_of::<siginfo_t>());
+        let siginfo_addr = addr;
+
+        addr = addr.wrapping_sub(size_of::<ucontext_t>()).align_down(16);
+        let ucontext_addr = addr;
+
+        if has_restorer {
+            addr = addr.wrapping_sub(size_of::<u64>());
+        }
+
+        SigFrame {
+            fpstate_addr,
+            siginfo_addr,
+            ucontext_addr,
+            stack_pointer: addr,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use super::Signal;
+use crate::{
+    prelude::*,
+    process::signal::{
+        c_types::{siginfo_t, sigval_t},
+        constants::SI_TIMER,
+        sig_num::SigNum,
+    },
+};
+
+/// A signal sent by a POSIX timer when it expires.
+///
+/// At most one signal of a timer is pending at a time. The expirations while the signal is
+/// pending are counted as overruns, which are reported when the signal is delivered.
+pub struct TimerSignal {
+    num: SigNum,
+    timer_id: i32,
+    value: sigval_t,
+    overrun: Arc<TimerOverrun>,
+}
+
+impl TimerSignal {
+    /// Creates a signal of the timer.
+    ///
+    /// The caller should check [`TimerOverrun::on_expire`] first, so that the signal is not
+    /// sent when there is already a pending one.
+    pub fn new(num: SigNum, timer_id: i32, value: sigval_t, overrun: Arc<TimerOverrun>) -> Self {
+        Self {
+            num,
+            timer_id,
+            value,
+            overrun,
+        }
+    }
+}
+
+impl Debug for TimerSignal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TimerSignal")
+            .field("num", &self.num)
+            .field("timer_id", &self.timer_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Signal for TimerSignal {
+    fn num(&self) -> SigNum {
+        self.num
+    }
+
+    fn to_info(&self) -> siginfo_t {
+        let mut info = siginfo_t::new(self.num, SI_TIMER);
+        info.set_si_timer(self.timer_id, self.overrun.on_deliver(), self.value);
+        info
+    }
+}
+
+impl Drop for TimerSignal {
+    fn drop(&mut self) {
+        // The signal is either delivered or discarded, so the timer can send a new one.
+        self.overrun.is_pending.store(false, Ordering::Release);
+    }
+}
+
+/// The overrun counter of a POSIX timer.
+#[derive(Debug, Default)]
+pub struct TimerOverrun {
+    is_pending: AtomicBool,
+    /// The number of the expirations since the pending signal was sent.
+    count: AtomicI32,
+    /// The overrun count of the last delivered signal.
+    last_count: AtomicI32,
+}
+
+impl TimerOverrun {
+    /// Records an expiration of the timer.
+    ///
+    /// Returns whether a new signal should be sent, i.e., whether there is no pending signal.
+    pub fn on_expire(&self) -> bool {
+        if !self.is_pending.swap(true, Ordering::AcqRel) {
+            return true;
+        }
+
+        // Like Linux, the count saturates at `DELAYTIMER_MAX` (i.e., `i32::MAX`).
+        let _ = self
+            .count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                count.checked_add(1)
+            });
+        false
+    }
+
+    /// Returns the overrun count of the last delivered signal.
+    pub fn last_count(&self) -> i32 {
+        self.last_count.load(Ordering::Relaxed)
+    }
+
+    /// Resets the counts when the timer is rearmed.
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.last_count.store(0, Ordering::Relaxed);
+    }
+
+    fn on_deliver(&self) -> i32 {
+        let count = self.count.swap(0, Ordering::Relaxed);
+        self.last_count.store(count, Ordering::Relaxed);
+        count
+    }
+}
@@ -58,11 +58,10 @@ impl Signal for UserSignal {
             UserSignalKind::Sigqueue => SI_QUEUE,
         };
 
-        siginfo_t::new(self.num, code)
-        // info.set_si_pid(self.pid);
-        // info.set_si_uid(self.uid);
-        // if let UserSignalKind::Sigqueue(val) = self.kind {
-        //     info.set_si_value(val);
-        // }
+        let mut info = siginfo_t::new(self.num, code);
+        info.set_si_pid(self.pid);
+        info.set_si_uid(self.uid);
+        // TODO: Set `si_value` for the signals sent by `sigqueue`.
+        info
     }
 }
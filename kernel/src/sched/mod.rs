@@ -3,9 +3,12 @@
 mod nice;
 mod sched_class;
 mod stats;
+pub mod watchdog;
 
 pub use self::{
     nice::{AtomicNice, Nice},
-    sched_class::{init, RealTimePolicy, RealTimePriority, SchedAttr, SchedPolicy},
+    sched_class::{
+        init, new_core_cookie, RealTimePolicy, RealTimePriority, SchedAttr, SchedPolicy,
+    },
     stats::{loadavg, nr_queued_and_running},
 };
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Core scheduling.
+//!
+//! Core scheduling ensures that only mutually-trusting tasks run on the SMT
+//! siblings of a physical core at the same time, which mitigates the
+//! side-channel attacks through the shared execution resources.
+//!
+//! Tasks are trusted by each other if they are tagged with the same _cookie_.
+//! The untagged tasks (whose cookies are zero) are trusted by each other as
+//! well. Idle tasks are trusted by everyone. If the task picked on a CPU is not
+//! trusted by the tasks running on the SMT siblings, the CPU is forced idle
+//! until the siblings switch to trusted tasks.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ostd::{
+    cpu::{all_cpus, smt_siblings, CpuId, CpuSet},
+    smp::inter_processor_call,
+    sync::SpinLock,
+};
+
+/// Whether any task has been tagged with a cookie.
+///
+/// Core scheduling is not enforced until then, since all the tasks are
+/// untagged and thus trust each other.
+static IS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Allocates a new unique cookie.
+pub fn new_cookie() -> u64 {
+    static NEXT_COOKIE: AtomicU64 = AtomicU64::new(1);
+
+    IS_ENABLED.store(true, Ordering::Relaxed);
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The core-scheduling state shared by the SMT siblings of a physical core.
+pub(super) struct CoreSched {
+    siblings: SpinLock<Vec<Sibling>>,
+}
+
+struct Sibling {
+    cpu: CpuId,
+    /// The cookie of the task running on the sibling.
+    ///
+    /// `None` means that the sibling is running an idle task or nothing.
+    cookie: Option<u64>,
+    /// Whether the sibling is forced idle because its task is not trusted.
+    is_forced_idle: bool,
+}
+
+impl CoreSched {
+    /// Creates the core-scheduling states of all the CPUs, where the SMT
+    /// siblings share the same state.
+    pub(super) fn new_for_all_cpus() -> Vec<Arc<CoreSched>> {
+        let mut cores: Vec<Option<Arc<CoreSched>>> = all_cpus().map(|_| None).collect();
+
+        for cpu in all_cpus() {
+            if cores[cpu.as_usize()].is_some() {
+                continue;
+            }
+
+            let siblings = smt_siblings(cpu);
+            let core = Arc::new(CoreSched {
+                siblings: SpinLock::new(
+                    siblings
+                        .iter()
+                        .map(|cpu| Sibling {
+                            cpu,
+                            cookie: None,
+                            is_forced_idle: false,
+                        })
+                        .collect(),
+                ),
+            });
+            for sibling in siblings.iter() {
+                cores[sibling.as_usize()] = Some(core.clone());
+            }
+        }
+
+        cores.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Tries to run a task with `cookie` on `cpu`.
+    ///
+    /// This method fails if the task is not trusted by the tasks running on the
+    /// SMT siblings, in which case `cpu` is recorded as forced idle. Otherwise,
+    /// the task is recorded as running on `cpu`. A `cookie` of `None` denotes
+    /// an idle task, which never fails.
+    pub(super) fn try_occupy(&self, cpu: CpuId, cookie: Option<u64>) -> bool {
+        if !IS_ENABLED.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let mut siblings = self.siblings.disable_irq().lock();
+
+        if let Some(cookie) = cookie {
+            let is_trusted = siblings.iter().all(|sibling| {
+                sibling.cpu == cpu || sibling.cookie.is_none_or(|other| other == cookie)
+            });
+            for sibling in siblings.iter_mut().filter(|sibling| sibling.cpu == cpu) {
+                sibling.is_forced_idle = !is_trusted;
+            }
+            if !is_trusted {
+                return false;
+            }
+        }
+
+        for sibling in siblings.iter_mut().filter(|sibling| sibling.cpu == cpu) {
+            sibling.cookie = cookie;
+        }
+        true
+    }
+
+    /// Records that no task is running on `cpu`.
+    ///
+    /// The SMT siblings that are forced idle are asked to reschedule, since
+    /// their tasks may be trusted now.
+    pub(super) fn release(&self, cpu: CpuId) {
+        if !IS_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut forced_idle = CpuSet::new_empty();
+        let mut siblings = self.siblings.disable_irq().lock();
+        for sibling in siblings.iter_mut() {
+            if sibling.cpu == cpu {
+                sibling.cookie = None;
+            } else if sibling.is_forced_idle {
+                sibling.is_forced_idle = false;
+                forced_idle.add(sibling.cpu);
+            }
+        }
+        drop(siblings);
+
+        if !forced_idle.is_empty() {
+            // A CPU forced idle runs its idle task, which picks the next task
+            // again after any interrupt.
+            inter_processor_call(&forced_idle, || {});
+        }
+    }
+}
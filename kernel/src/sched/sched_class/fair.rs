@@ -252,6 +252,19 @@ impl SchedClassRq for FairClassRq {
         Some(entity)
     }
 
+    fn remove(&mut self, task: &Arc<Task>) -> bool {
+        let len = self.entities.len();
+        self.entities
+            .retain(|Reverse(FairQueueItem(entity, _))| !Arc::ptr_eq(entity, task));
+        if self.entities.len() == len {
+            return false;
+        }
+
+        let sched_attr = task.as_thread().unwrap().sched_attr();
+        self.total_weight -= sched_attr.fair.weight.load(Relaxed);
+        true
+    }
+
     fn update_current(
         &mut self,
         rt: &CurrentRuntime,
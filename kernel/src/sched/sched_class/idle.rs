@@ -54,6 +54,19 @@ impl SchedClassRq for IdleClassRq {
         self.entity.take()
     }
 
+    fn remove(&mut self, task: &Arc<Task>) -> bool {
+        if self
+            .entity
+            .as_ref()
+            .is_some_and(|entity| Arc::ptr_eq(entity, task))
+        {
+            self.entity = None;
+            true
+        } else {
+            false
+        }
+    }
+
     fn update_current(&mut self, _: &CurrentRuntime, _: &SchedAttr, _flags: UpdateFlags) -> bool {
         // Idle entities has the greatest priority value. They should always be preempted.
         true
@@ -3,11 +3,16 @@
 #![warn(unused)]
 
 use alloc::{boxed::Box, sync::Arc};
-use core::{fmt, sync::atomic::Ordering};
+use core::{
+    cmp, fmt,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
 
 use ostd::{
     arch::read_tsc as sched_clock,
-    cpu::{all_cpus, CpuId, PinCurrentCpu},
+    cpu::{all_cpus, CpuId, CpuSet, PinCurrentCpu},
+    numa::{node_of_cpu, NodeId},
     sync::SpinLock,
     task::{
         scheduler::{
@@ -16,6 +21,7 @@ use ostd::{
         },
         AtomicCpuId, Task,
     },
+    timer::Jiffies,
     trap::disable_local,
 };
 
@@ -23,8 +29,13 @@ use super::{
     nice::Nice,
     stats::{set_stats_from_scheduler, SchedulerStats},
 };
-use crate::thread::{AsThread, Thread};
+use crate::{
+    process::posix_thread::AsPosixThread,
+    thread::{AsThread, Thread},
+};
 
+mod core_sched;
+mod numa;
 mod policy;
 mod time;
 
@@ -33,11 +44,15 @@ mod idle;
 mod real_time;
 mod stop;
 
-use self::policy::{SchedPolicyKind, SchedPolicyState};
 pub use self::{
+    core_sched::new_cookie as new_core_cookie,
     policy::SchedPolicy,
     real_time::{RealTimePolicy, RealTimePriority},
 };
+use self::{
+    core_sched::CoreSched,
+    policy::{SchedPolicyKind, SchedPolicyState},
+};
 
 type SchedEntity = (Arc<Task>, Arc<Thread>);
 
@@ -70,6 +85,15 @@ struct PerCpuClassRqSet {
     fair: fair::FairClassRq,
     idle: idle::IdleClassRq,
     current: Option<(SchedEntity, CurrentRuntime)>,
+    cpu: CpuId,
+    /// The core-scheduling state shared with the SMT siblings.
+    core: Arc<CoreSched>,
+    /// The number of threads that went to the uninterruptible sleep on this
+    /// CPU minus those that were woken up onto this CPU.
+    ///
+    /// The value of a single CPU is meaningless and may be negative, but the
+    /// sum over all CPUs is the number of threads in the uninterruptible sleep.
+    nr_uninterruptible: i32,
 }
 
 /// Stores the runtime information of the current task.
@@ -118,6 +142,11 @@ trait SchedClassRq: Send + fmt::Debug {
     /// Picks the next task for running.
     fn pick_next(&mut self) -> Option<Arc<Task>>;
 
+    /// Removes a task from the run queue.
+    ///
+    /// Returns `true` if the task was found in the run queue.
+    fn remove(&mut self, task: &Arc<Task>) -> bool;
+
     /// Update the information of the current task.
     fn update_current(&mut self, rt: &CurrentRuntime, attr: &SchedAttr, flags: UpdateFlags)
         -> bool;
@@ -133,6 +162,13 @@ pub struct SchedAttr {
     last_cpu: AtomicCpuId,
     real_time: real_time::RealTimeAttr,
     fair: fair::FairAttr,
+    core_cookie: AtomicU64,
+    numa: numa::NumaStats,
+    /// Whether the thread is in the uninterruptible sleep and thus counted in
+    /// the system load.
+    contributes_to_load: AtomicBool,
+    /// The jiffies when the thread last went to the uninterruptible sleep.
+    sleep_start: AtomicU64,
 }
 
 impl SchedAttr {
@@ -152,14 +188,37 @@ impl SchedAttr {
                 SchedPolicy::Fair(nice) => nice,
                 _ => Nice::default(),
             }),
+            core_cookie: AtomicU64::new(0),
+            numa: numa::NumaStats::new(),
+            contributes_to_load: AtomicBool::new(false),
+            sleep_start: AtomicU64::new(0),
         }
     }
 
+    /// Returns when the thread went to the uninterruptible sleep, or `None`
+    /// if the thread is not in the uninterruptible sleep.
+    pub fn uninterruptible_sleep_start(&self) -> Option<Jiffies> {
+        self.contributes_to_load
+            .load(Ordering::Acquire)
+            .then(|| Jiffies::new(self.sleep_start.load(Ordering::Relaxed)))
+    }
+
     /// Retrieves the current scheduling policy of the thread.
+    ///
+    /// This is the policy chosen by the user, which does not include any
+    /// priority inherited from the waiters of priority-inheritance locks.
     pub fn policy(&self) -> SchedPolicy {
         self.policy.get()
     }
 
+    /// Retrieves the effective scheduling policy of the thread.
+    ///
+    /// This is the policy that the thread is actually scheduled with, taking
+    /// the inherited priorities into account.
+    pub fn effective_policy(&self) -> SchedPolicy {
+        self.policy.effective()
+    }
+
     fn policy_kind(&self) -> SchedPolicyKind {
         self.policy.kind()
     }
@@ -169,17 +228,75 @@ impl SchedAttr {
     /// Specifically for real-time policies, if the new policy doesn't
     /// specify a base slice factor for RR, the old one will be kept.
     pub fn set_policy(&self, policy: SchedPolicy) {
-        self.policy.set(policy, |policy| match policy {
+        self.policy.set(policy, |policy| self.apply_policy(policy));
+    }
+
+    /// Lends `policy` to the thread on behalf of the lock identified by `lock_id`.
+    ///
+    /// Returns `true` if the effective policy changes.
+    fn inherit_policy(&self, lock_id: usize, policy: SchedPolicy) -> bool {
+        self.policy
+            .inherit(lock_id, policy, |policy| self.apply_policy(policy))
+    }
+
+    /// Revokes the policy lent on behalf of the lock identified by `lock_id`.
+    ///
+    /// Returns `true` if the effective policy changes.
+    fn revoke_policy(&self, lock_id: usize) -> bool {
+        self.policy
+            .revoke(lock_id, |policy| self.apply_policy(policy))
+    }
+
+    fn apply_policy(&self, policy: SchedPolicy) {
+        match policy {
             SchedPolicy::RealTime { rt_prio, rt_policy } => {
                 self.real_time.update(rt_prio.get(), rt_policy);
             }
             SchedPolicy::Fair(nice) => self.fair.update(nice),
             _ => {}
-        });
+        }
     }
 
-    pub fn update_policy<T>(&self, f: impl FnOnce(&mut SchedPolicy) -> T) -> T {
-        self.policy.update(f)
+    /// Returns the time slice of the thread.
+    ///
+    /// For fair threads, this is the base time slice, since the actual time
+    /// slice varies with the load of the run queue.
+    pub fn time_slice(&self) -> Duration {
+        match self.policy() {
+            SchedPolicy::RealTime { rt_policy, .. } => rt_policy.time_slice(),
+            SchedPolicy::Fair(_) => Duration::from_nanos(time::BASE_SLICE_NS),
+            SchedPolicy::Stop | SchedPolicy::Idle => Duration::ZERO,
+        }
+    }
+
+    /// Returns the core-scheduling cookie of the thread.
+    ///
+    /// Threads are allowed to run on the SMT siblings of a physical core at the
+    /// same time only if they have the same cookie. Zero means that the thread
+    /// is untagged.
+    pub fn core_cookie(&self) -> u64 {
+        self.core_cookie.load(Ordering::Relaxed)
+    }
+
+    /// Sets the core-scheduling cookie of the thread.
+    ///
+    /// The new cookie takes effect the next time the thread is scheduled.
+    pub fn set_core_cookie(&self, cookie: u64) {
+        self.core_cookie.store(cookie, Ordering::Relaxed);
+    }
+
+    /// Records a page fault on a page residing on the NUMA node.
+    ///
+    /// The thread tends to be placed on the node where most of its faults
+    /// happen.
+    pub fn record_numa_fault(&self, node_id: NodeId) {
+        self.numa.record_fault(node_id);
+    }
+
+    /// Returns the NUMA node where most of the faults of the thread happen
+    /// recently, if any.
+    pub fn preferred_numa_node(&self) -> Option<NodeId> {
+        self.numa.preferred_node()
     }
 
     fn last_cpu(&self) -> Option<CpuId> {
@@ -218,10 +335,19 @@ impl Scheduler for ClassScheduler {
             .current
             .as_ref()
             .is_none_or(|((_, rq_current_thread), _)| {
-                thread.sched_attr().policy() < rq_current_thread.sched_attr().policy()
+                thread.sched_attr().effective_policy()
+                    < rq_current_thread.sched_attr().effective_policy()
             });
 
         thread.sched_attr().set_last_cpu(cpu);
+        if flags == EnqueueFlags::Wake
+            && thread
+                .sched_attr()
+                .contributes_to_load
+                .swap(false, Ordering::Relaxed)
+        {
+            rq.nr_uninterruptible -= 1;
+        }
         rq.enqueue_entity((task, thread), Some(flags));
 
         should_preempt.then_some(cpu)
@@ -237,17 +363,60 @@ impl Scheduler for ClassScheduler {
         let guard = disable_local();
         f(&*self.rqs[guard.current_cpu().as_usize()].lock())
     }
+
+    fn cmp_priority(&self, a: &Task, b: &Task) -> cmp::Ordering {
+        let (Some(a), Some(b)) = (a.as_thread(), b.as_thread()) else {
+            return cmp::Ordering::Equal;
+        };
+
+        // A smaller policy value means a higher priority.
+        let a = a.sched_attr().effective_policy();
+        let b = b.sched_attr().effective_policy();
+        b.cmp(&a)
+    }
+
+    fn inherit_priority(&self, task: &Arc<Task>, lock_id: usize, donor: &Task) -> Option<CpuId> {
+        let thread = task.as_thread()?;
+        let policy = donor.as_thread()?.sched_attr().effective_policy();
+
+        // Only the priorities of the real-time classes are lent. Lending a
+        // fair policy would require adjusting the weights of the fair run
+        // queues, which is not worth it since fair threads are not subject to
+        // priority inversion in the first place.
+        if !matches!(
+            policy.kind(),
+            SchedPolicyKind::Stop | SchedPolicyKind::RealTime
+        ) {
+            return None;
+        }
+
+        self.reprioritize(task, || thread.sched_attr().inherit_policy(lock_id, policy))
+    }
+
+    fn revoke_priority(&self, task: &Arc<Task>, lock_id: usize) {
+        let Some(thread) = task.as_thread() else {
+            return;
+        };
+
+        // The lowered priority of a running task takes effect on the next
+        // tick, so there is no need to preempt here.
+        let _ = self.reprioritize(task, || thread.sched_attr().revoke_policy(lock_id));
+    }
 }
 
 impl ClassScheduler {
     pub fn new() -> Self {
-        let class_rq = |cpu| {
+        let cores = CoreSched::new_for_all_cpus();
+        let class_rq = |cpu: CpuId| {
             SpinLock::new(PerCpuClassRqSet {
                 stop: stop::StopClassRq::new(),
                 real_time: real_time::RealTimeClassRq::new(cpu),
                 fair: fair::FairClassRq::new(cpu),
                 idle: idle::IdleClassRq::new(),
                 current: None,
+                cpu,
+                core: cores[cpu.as_usize()].clone(),
+                nr_uninterruptible: 0,
             })
         };
         ClassScheduler {
@@ -256,14 +425,75 @@ impl ClassScheduler {
         }
     }
 
+    /// Changes the effective policy of a task with `change`, and moves the task
+    /// to the right run queue if it is waiting in one.
+    ///
+    /// The `change` closure should return whether the effective policy
+    /// changes. If the current task on the CPU of `task` needs to be
+    /// preempted, the id of the CPU is returned.
+    fn reprioritize(&self, task: &Arc<Task>, change: impl FnOnce() -> bool) -> Option<CpuId> {
+        // The task is not in any run queue. The new policy will be used when it
+        // is enqueued.
+        let Some(cpu) = task.cpu().get() else {
+            change();
+            return None;
+        };
+
+        let mut rq = self.rqs[cpu.as_usize()].disable_irq().lock();
+        if !change() {
+            return None;
+        }
+
+        let rq_current_thread = match rq.current.as_ref() {
+            Some(((rq_current_task, _), _)) if Arc::ptr_eq(rq_current_task, task) => {
+                return None;
+            }
+            Some(((_, rq_current_thread), _)) => Some(rq_current_thread.clone()),
+            None => None,
+        };
+        if !rq.dequeue_entity(task) {
+            return None;
+        }
+
+        let thread = task.as_thread()?.clone();
+        let should_preempt = rq_current_thread.is_none_or(|rq_current_thread| {
+            thread.sched_attr().effective_policy()
+                < rq_current_thread.sched_attr().effective_policy()
+        });
+        rq.enqueue_entity((task.clone(), thread), None);
+
+        should_preempt.then_some(cpu)
+    }
+
     // TODO: Implement a better algorithm and replace the current naive implementation.
     fn select_cpu(&self, thread: &Thread, flags: EnqueueFlags) -> CpuId {
+        let affinity = thread.atomic_cpu_affinity().load(Ordering::Relaxed);
+        // The CPUs on the node where most of the memory accessed by the thread resides.
+        let node_affinity = thread
+            .sched_attr()
+            .numa
+            .preferred_node()
+            .and_then(|node_id| {
+                let mut cpus = CpuSet::new_empty();
+                for cpu in affinity.iter().filter(|&cpu| node_of_cpu(cpu) == node_id) {
+                    cpus.add(cpu);
+                }
+                (!cpus.is_empty()).then_some(cpus)
+            });
+
         if let Some(last_cpu) = thread.sched_attr().last_cpu() {
-            return last_cpu;
+            // A waking thread is moved to its preferred node if it is not there.
+            let should_migrate = flags == EnqueueFlags::Wake
+                && node_affinity
+                    .as_ref()
+                    .is_some_and(|cpus| !cpus.contains(last_cpu));
+            if !should_migrate {
+                return last_cpu;
+            }
         }
-        debug_assert!(flags == EnqueueFlags::Spawn);
+        let affinity = node_affinity.unwrap_or(affinity);
+
         let guard = disable_local();
-        let affinity = thread.atomic_cpu_affinity().load(Ordering::Relaxed);
         let mut selected = guard.current_cpu();
         let mut minimum_load = u32::MAX;
         let last_chosen = match self.last_chosen_cpu.get() {
@@ -317,6 +547,31 @@ impl PerCpuClassRqSet {
         }
     }
 
+    /// Removes a task waiting in any of the run queues of the scheduling classes.
+    ///
+    /// Returns `true` if the task was found.
+    fn dequeue_entity(&mut self, task: &Arc<Task>) -> bool {
+        self.stop.remove(task)
+            || self.real_time.remove(task)
+            || self.fair.remove(task)
+            || self.idle.remove(task)
+    }
+
+    /// Checks if a queued task has a priority no lower than `policy`.
+    ///
+    /// For a fair or idle `policy`, this method always returns `true`, leaving
+    /// the decision to the scheduling classes.
+    fn has_entity_as_high_as(&self, policy: SchedPolicy) -> bool {
+        match policy {
+            SchedPolicy::Stop => !self.stop.is_empty(),
+            SchedPolicy::RealTime { rt_prio, .. } => {
+                !self.stop.is_empty()
+                    || (self.real_time.highest_prio()).is_some_and(|prio| prio <= rt_prio.get())
+            }
+            SchedPolicy::Fair(_) | SchedPolicy::Idle => true,
+        }
+    }
+
     fn nr_queued_and_running(&self) -> (u32, u32) {
         let queued = self.stop.len() + self.real_time.len() + self.fair.len() + self.idle.len();
         let running = usize::from(self.current.is_some());
@@ -330,14 +585,33 @@ impl LocalRunQueue for PerCpuClassRqSet {
     }
 
     fn pick_next_current(&mut self) -> Option<&Arc<Task>> {
-        self.pick_next_entity().and_then(|next| {
-            // We guarantee that a task can appear at once in a `PerCpuClassRqSet`. So, the `next` cannot be the same
-            // as the current task here.
-            if let Some((old, _)) = self.current.replace((next, CurrentRuntime::new())) {
-                self.enqueue_entity(old, None);
+        // A runnable current task keeps running if no queued task has a
+        // priority as high as it, e.g., when a real-time task yields.
+        if let Some(((_, cur), _)) = self.current.as_ref() {
+            let policy = cur.sched_attr().effective_policy();
+            if !self.has_entity_as_high_as(policy) {
+                return None;
             }
-            self.current.as_ref().map(|((task, _), _)| task)
-        })
+        }
+
+        let mut next = self.pick_next_entity()?;
+        if !self.core.try_occupy(self.cpu, core_cookie_of(&next.1)) {
+            // The task is not trusted by the tasks running on the SMT siblings.
+            // Force the CPU idle.
+            self.enqueue_entity(next, None);
+            next = self.idle.pick_next().and_then(|task| {
+                let thread = task.as_thread()?.clone();
+                Some((task, thread))
+            })?;
+            self.core.release(self.cpu);
+        }
+
+        // We guarantee that a task can appear at once in a `PerCpuClassRqSet`. So, the `next` cannot be the same
+        // as the current task here.
+        if let Some((old, _)) = self.current.replace((next, CurrentRuntime::new())) {
+            self.enqueue_entity(old, None);
+        }
+        self.current.as_ref().map(|((task, _), _)| task)
     }
 
     fn update_current(&mut self, flags: UpdateFlags) -> bool {
@@ -362,13 +636,39 @@ impl LocalRunQueue for PerCpuClassRqSet {
     }
 
     fn dequeue_current(&mut self) -> Option<Arc<Task>> {
-        self.current.take().map(|((cur_task, _), _)| {
+        self.core.release(self.cpu);
+        self.current.take().map(|((cur_task, cur_thread), _)| {
+            if is_uninterruptible_sleep(&cur_thread) {
+                let attr = cur_thread.sched_attr();
+                attr.sleep_start
+                    .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+                attr.contributes_to_load.store(true, Ordering::Release);
+                self.nr_uninterruptible += 1;
+            }
             cur_task.schedule_info().cpu.set_to_none();
             cur_task
         })
     }
 }
 
+/// Checks if a thread leaving the CPU goes to the uninterruptible sleep.
+///
+/// Only POSIX threads are taken into account. Kernel threads always sleep
+/// uninterruptibly, even when they are idle, so counting them would make the
+/// load meaningless.
+fn is_uninterruptible_sleep(thread: &Thread) -> bool {
+    let Some(posix_thread) = thread.as_posix_thread() else {
+        return false;
+    };
+    !thread.is_exited() && !thread.is_stopped() && !posix_thread.is_interruptible_wait()
+}
+
+/// Returns the cookie used for core scheduling, or `None` for idle threads.
+fn core_cookie_of(thread: &Thread) -> Option<u64> {
+    let attr = thread.sched_attr();
+    (attr.policy_kind() != SchedPolicyKind::Idle).then(|| attr.core_cookie())
+}
+
 impl SchedulerStats for ClassScheduler {
     fn nr_queued_and_running(&self) -> (u32, u32) {
         self.rqs.iter().fold((0, 0), |(queued, running), rq| {
@@ -376,6 +676,17 @@ impl SchedulerStats for ClassScheduler {
             (queued + q, running + r)
         })
     }
+
+    fn nr_uninterruptible(&self) -> u32 {
+        let nr_uninterruptible = self
+            .rqs
+            .iter()
+            .map(|rq| rq.lock().nr_uninterruptible as i64)
+            .sum::<i64>();
+        // The per-CPU counters are read at different times, so the sum may
+        // be slightly off.
+        nr_uninterruptible.max(0) as u32
+    }
 }
 
 impl Default for ClassScheduler {
@@ -383,3 +694,80 @@ impl Default for ClassScheduler {
         Self::new()
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::thread::kernel_thread::ThreadOptions;
+
+    const LOCK_A: usize = 1;
+    const LOCK_B: usize = 2;
+
+    fn new_task(policy: SchedPolicy) -> Arc<Task> {
+        ThreadOptions::new(|| {}).sched_policy(policy).build()
+    }
+
+    fn real_time(prio: u8) -> SchedPolicy {
+        SchedPolicy::RealTime {
+            rt_prio: RealTimePriority::new(prio),
+            rt_policy: RealTimePolicy::Fifo,
+        }
+    }
+
+    fn effective_policy(task: &Task) -> SchedPolicy {
+        task.as_thread().unwrap().sched_attr().effective_policy()
+    }
+
+    #[ktest]
+    fn priority_inheritance() {
+        let scheduler = ClassScheduler::new();
+        let holder = new_task(SchedPolicy::Fair(Nice::default()));
+        let high_waiter = new_task(real_time(10));
+        let low_waiter = new_task(real_time(50));
+        let fair_waiter = new_task(SchedPolicy::Fair(Nice::MIN));
+
+        // Only the real-time priorities are lent.
+        assert!(scheduler
+            .inherit_priority(&holder, LOCK_A, &fair_waiter)
+            .is_none());
+        assert_eq!(
+            effective_policy(&holder),
+            SchedPolicy::Fair(Nice::default())
+        );
+
+        // The holder is boosted to the highest priority lent by the waiters.
+        scheduler.inherit_priority(&holder, LOCK_A, &low_waiter);
+        assert_eq!(effective_policy(&holder), real_time(50));
+        scheduler.inherit_priority(&holder, LOCK_B, &high_waiter);
+        assert_eq!(effective_policy(&holder), real_time(10));
+        assert_eq!(
+            scheduler.cmp_priority(&holder, &high_waiter),
+            cmp::Ordering::Equal
+        );
+        assert_eq!(
+            scheduler.cmp_priority(&holder, &low_waiter),
+            cmp::Ordering::Greater
+        );
+
+        // The policy chosen by the user is kept.
+        assert_eq!(
+            holder.as_thread().unwrap().sched_attr().policy(),
+            SchedPolicy::Fair(Nice::default())
+        );
+
+        // Revoking the priority lent on behalf of a lock keeps the others.
+        scheduler.revoke_priority(&holder, LOCK_B);
+        assert_eq!(effective_policy(&holder), real_time(50));
+        scheduler.revoke_priority(&holder, LOCK_A);
+        assert_eq!(
+            effective_policy(&holder),
+            SchedPolicy::Fair(Nice::default())
+        );
+        assert_eq!(
+            scheduler.cmp_priority(&holder, &low_waiter),
+            cmp::Ordering::Less
+        );
+    }
+}
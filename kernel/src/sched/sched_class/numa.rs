@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! NUMA fault statistics.
+//!
+//! The page faults of a thread are accounted to the NUMA nodes where the
+//! faulting pages reside. The node holding most of the memory accessed by the
+//! thread recently is its _preferred node_, on whose CPUs the scheduler tries
+//! to place the thread.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use ostd::{
+    numa::{num_nodes, NodeId},
+    sync::SpinLock,
+};
+
+/// The value of `NumaStats::preferred` if there is no preferred node.
+const NO_PREFERRED_NODE: u32 = u32::MAX;
+
+/// The number of faults needed before a node can be preferred.
+const MIN_FAULTS_TO_PREFER: u32 = 16;
+
+/// The number of faults at which the statistics decay by half, so that the
+/// recent faults weigh more.
+const FAULTS_TO_DECAY: u32 = 1024;
+
+pub(super) struct NumaStats {
+    /// The numbers of the faults on each node.
+    faults: SpinLock<Vec<u32>>,
+    preferred: AtomicU32,
+}
+
+impl NumaStats {
+    pub(super) const fn new() -> Self {
+        Self {
+            faults: SpinLock::new(Vec::new()),
+            preferred: AtomicU32::new(NO_PREFERRED_NODE),
+        }
+    }
+
+    pub(super) fn record_fault(&self, node_id: NodeId) {
+        let mut faults = self.faults.lock();
+        if faults.len() < num_nodes() {
+            faults.resize(num_nodes(), 0);
+        }
+        faults[node_id.as_usize()] += 1;
+
+        let mut total: u32 = faults.iter().sum();
+        if total >= FAULTS_TO_DECAY {
+            faults.iter_mut().for_each(|nr_faults| *nr_faults /= 2);
+            total = faults.iter().sum();
+        }
+
+        // A node is preferred only if it holds the majority of the faults.
+        let (max_node, max_faults) = (faults.iter().enumerate())
+            .max_by_key(|(_, nr_faults)| **nr_faults)
+            .unwrap();
+        let preferred = if total >= MIN_FAULTS_TO_PREFER && *max_faults * 2 > total {
+            max_node as u32
+        } else {
+            NO_PREFERRED_NODE
+        };
+        self.preferred.store(preferred, Ordering::Relaxed);
+    }
+
+    pub(super) fn preferred_node(&self) -> Option<NodeId> {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        if preferred == NO_PREFERRED_NODE {
+            return None;
+        }
+        NodeId::try_from(preferred as usize).ok()
+    }
+}
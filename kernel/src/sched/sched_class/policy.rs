@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicU8, Ordering::Relaxed};
+use alloc::collections::BTreeMap;
+use core::{
+    cmp,
+    sync::atomic::{AtomicU8, Ordering::Relaxed},
+};
 
 use atomic_integer_wrapper::define_atomic_version_of_integer_like_type;
 use int_to_c_enum::TryFromInt;
@@ -54,17 +58,42 @@ impl From<SchedPolicyKind> for u8 {
     }
 }
 
+/// The scheduling policy of a thread, together with the policies lent to it
+/// through priority inheritance.
+///
+/// The _base_ policy is the one chosen by the user, while the _effective_
+/// policy is the one with the highest priority among the base policy and all
+/// the inherited ones. The scheduling classes always work with the effective
+/// policy.
 #[derive(Debug)]
 pub(super) struct SchedPolicyState {
+    /// The kind of the effective policy.
     kind: AtomicSchedPolicyKind,
-    policy: SpinLock<SchedPolicy>,
+    policy: SpinLock<PolicyInner>,
+}
+
+#[derive(Debug)]
+struct PolicyInner {
+    base: SchedPolicy,
+    /// The policies lent by the waiters of priority-inheritance locks, keyed
+    /// by the identifiers of the locks.
+    inherited: BTreeMap<usize, SchedPolicy>,
+}
+
+impl PolicyInner {
+    fn effective(&self) -> SchedPolicy {
+        self.inherited.values().copied().fold(self.base, cmp::min)
+    }
 }
 
 impl SchedPolicyState {
     pub fn new(policy: SchedPolicy) -> Self {
         Self {
             kind: AtomicSchedPolicyKind::new(policy.kind()),
-            policy: SpinLock::new(policy),
+            policy: SpinLock::new(PolicyInner {
+                base: policy,
+                inherited: BTreeMap::new(),
+            }),
         }
     }
 
@@ -72,10 +101,19 @@ impl SchedPolicyState {
         self.kind.load(Relaxed)
     }
 
+    /// Returns the base policy.
     pub fn get(&self) -> SchedPolicy {
-        *self.policy.disable_irq().lock()
+        self.policy.disable_irq().lock().base
     }
 
+    /// Returns the effective policy.
+    pub fn effective(&self) -> SchedPolicy {
+        self.policy.disable_irq().lock().effective()
+    }
+
+    /// Sets the base policy.
+    ///
+    /// The `update` closure is called with the new effective policy.
     pub fn set(&self, mut policy: SchedPolicy, update: impl FnOnce(SchedPolicy)) {
         let mut this = self.policy.disable_irq().lock();
 
@@ -92,17 +130,69 @@ impl SchedPolicyState {
                 rt_policy: RealTimePolicy::RoundRobin { base_slice_factor },
                 ..
             },
-        ) = (*this, &mut policy)
+        ) = (this.base, &mut policy)
         {
             *base_slice_factor = slot.or(*base_slice_factor);
         }
 
-        update(policy);
-        self.kind.store(policy.kind(), Relaxed);
-        *this = policy;
+        this.base = policy;
+        let effective = this.effective();
+        update(effective);
+        self.kind.store(effective.kind(), Relaxed);
     }
 
-    pub fn update<T>(&self, update: impl FnOnce(&mut SchedPolicy) -> T) -> T {
-        update(&mut *self.policy.disable_irq().lock())
+    /// Lends `policy` to the thread on behalf of the lock identified by
+    /// `lock_id`.
+    ///
+    /// If the lock has already lent a policy, the one with the higher priority
+    /// is kept. The `update` closure is called with the new effective policy if
+    /// the effective policy changes, in which case this method returns `true`.
+    pub fn inherit(
+        &self,
+        lock_id: usize,
+        policy: SchedPolicy,
+        update: impl FnOnce(SchedPolicy),
+    ) -> bool {
+        self.change_inherited(
+            |inherited| {
+                inherited
+                    .entry(lock_id)
+                    .and_modify(|lent| *lent = cmp::min(*lent, policy))
+                    .or_insert(policy);
+            },
+            update,
+        )
+    }
+
+    /// Revokes the policy lent on behalf of the lock identified by `lock_id`.
+    ///
+    /// The `update` closure is called with the new effective policy if the
+    /// effective policy changes, in which case this method returns `true`.
+    pub fn revoke(&self, lock_id: usize, update: impl FnOnce(SchedPolicy)) -> bool {
+        self.change_inherited(
+            |inherited| {
+                inherited.remove(&lock_id);
+            },
+            update,
+        )
+    }
+
+    fn change_inherited(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<usize, SchedPolicy>),
+        update: impl FnOnce(SchedPolicy),
+    ) -> bool {
+        let mut this = self.policy.disable_irq().lock();
+
+        let old = this.effective();
+        change(&mut this.inherited);
+        let new = this.effective();
+        if old == new {
+            return false;
+        }
+
+        update(new);
+        self.kind.store(new.kind(), Relaxed);
+        true
     }
 }
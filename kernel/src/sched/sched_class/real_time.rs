@@ -5,6 +5,7 @@ use core::{
     array,
     num::NonZero,
     sync::atomic::{AtomicU64, AtomicU8, Ordering::Relaxed},
+    time::Duration,
 };
 
 use bitvec::{bitarr, BitArr};
@@ -16,7 +17,10 @@ use ostd::{
     },
 };
 
-use super::{time::base_slice_clocks, CurrentRuntime, SchedAttr, SchedClassRq};
+use super::{
+    time::{base_slice_clocks, BASE_SLICE_NS},
+    CurrentRuntime, SchedAttr, SchedClassRq,
+};
 use crate::{sched::nice::RangedU8, thread::AsThread};
 
 pub type RealTimePriority = RangedU8<1, 99>;
@@ -48,6 +52,19 @@ impl RealTimePolicy {
             RealTimePolicy::Fifo => 0,
         }
     }
+
+    /// Returns the time slice of the policy, or zero for FIFO threads which
+    /// have no time slice.
+    pub fn time_slice(self) -> Duration {
+        match self {
+            RealTimePolicy::RoundRobin { base_slice_factor } => Duration::from_nanos(
+                BASE_SLICE_NS
+                    * base_slice_factor
+                        .map_or(DEFAULT_BASE_SLICE_FACTOR, |factor| u64::from(factor.get())),
+            ),
+            RealTimePolicy::Fifo => Duration::ZERO,
+        }
+    }
 }
 
 /// The scheduling attribute for the REAL-TIME scheduling class.
@@ -116,6 +133,10 @@ impl PrioArray {
         }
     }
 
+    fn highest_prio(&self) -> Option<u8> {
+        self.map.first_one().map(|prio| prio as u8)
+    }
+
     fn pop(&mut self) -> Option<Arc<Task>> {
         let mut iter = self.map.iter_ones();
         let prio = iter.next()? as u8;
@@ -128,6 +149,26 @@ impl PrioArray {
         }
         Some(thread)
     }
+
+    fn remove(&mut self, task: &Arc<Task>) -> bool {
+        // The priority may have changed since the task was enqueued, so all
+        // the non-empty queues are searched.
+        let found = self.map.iter_ones().find_map(|prio| {
+            let queue = &self.queue[prio];
+            let index = queue.iter().position(|queued| Arc::ptr_eq(queued, task))?;
+            Some((prio, index))
+        });
+        let Some((prio, index)) = found else {
+            return false;
+        };
+
+        let queue = &mut self.queue[prio];
+        queue.remove(index);
+        if queue.is_empty() {
+            self.map.set(prio, false);
+        }
+        true
+    }
 }
 
 /// The per-cpu run queue for the REAL-TIME scheduling class.
@@ -174,6 +215,16 @@ impl RealTimeClassRq {
     fn swap_arrays(&mut self) {
         self.index = !self.index;
     }
+
+    /// Returns the highest priority of the queued threads.
+    ///
+    /// Note that a smaller value means a higher priority.
+    pub fn highest_prio(&self) -> Option<u8> {
+        self.array
+            .iter()
+            .filter_map(|array| array.highest_prio())
+            .min()
+    }
 }
 
 impl SchedClassRq for RealTimeClassRq {
@@ -197,12 +248,34 @@ impl SchedClassRq for RealTimeClassRq {
             return None;
         }
 
-        (self.active_array().pop())
-            .or_else(|| {
+        // Threads with a higher priority always go first. Among the threads
+        // with the same priority, the ones in the active array go first, so
+        // that a thread that has used up its time slice or yielded is put at
+        // the tail.
+        let active_prio = self.active_array().highest_prio();
+        let inactive_prio = self.inactive_array().highest_prio();
+        let array = match (active_prio, inactive_prio) {
+            (Some(active), Some(inactive)) if inactive < active => self.inactive_array(),
+            (Some(_), _) => self.active_array(),
+            (None, _) => {
                 self.swap_arrays();
-                self.active_array().pop()
-            })
-            .inspect(|_| self.nr_running -= 1)
+                self.active_array()
+            }
+        };
+
+        let next = array.pop();
+        if next.is_some() {
+            self.nr_running -= 1;
+        }
+        next
+    }
+
+    fn remove(&mut self, task: &Arc<Task>) -> bool {
+        let removed = self.array.iter_mut().any(|array| array.remove(task));
+        if removed {
+            self.nr_running -= 1;
+        }
+        removed
     }
 
     fn update_current(
@@ -215,8 +288,9 @@ impl SchedClassRq for RealTimeClassRq {
 
         match flags {
             UpdateFlags::Tick | UpdateFlags::Wait => match attr.time_slice.load(Relaxed) {
-                0 => (self.inactive_array().map.iter_ones().next())
-                    .is_some_and(|prio| prio > usize::from(attr.prio.load(Relaxed))),
+                0 => self
+                    .highest_prio()
+                    .is_some_and(|prio| prio < attr.prio.load(Relaxed)),
                 ts => ts <= rt.period_delta,
             },
             UpdateFlags::Yield => true,
@@ -48,12 +48,19 @@ where
     let jiffies = timer::Jiffies::elapsed().as_u64();
 
     // Return if the load average was updated less than 5 seconds ago.
-    if jiffies < LOAD_AVG_NEXT_UPDATE.load(Relaxed) {
+    let next_update = LOAD_AVG_NEXT_UPDATE.load(Relaxed);
+    if jiffies < next_update {
         return;
     }
 
-    // Update the next time the load average will be updated (now + 5sec)
-    LOAD_AVG_NEXT_UPDATE.store(jiffies + LOAD_FREQ, Relaxed);
+    // Update the next time the load average will be updated (now + 5sec).
+    // Only one caller wins if the update races, so the load is sampled once per interval.
+    if LOAD_AVG_NEXT_UPDATE
+        .compare_exchange(next_update, jiffies + LOAD_FREQ, Relaxed, Relaxed)
+        .is_err()
+    {
+        return;
+    }
 
     // Get the fixed-point representation of the load
     let new_load = FixedPoint::from_num(get_load());
@@ -66,6 +73,46 @@ where
     load[2] = calc_loadavg(load[2], EXP_15, new_load);
 }
 
+/// Calculates `old_load * exp + new_load * (1 - exp)`.
+///
+/// The result is rounded up if the load is increasing, so that a constant load
+/// is eventually reached instead of being approached forever. Otherwise, it is
+/// rounded down.
 fn calc_loadavg(old_load: FixedPoint, exp: FixedPoint, new_load: FixedPoint) -> FixedPoint {
-    old_load * exp + new_load * (FixedPoint::ONE - exp)
+    let one = FixedPoint::ONE.to_bits() as u64;
+    let (old, exp, new) = (
+        old_load.to_bits() as u64,
+        exp.to_bits() as u64,
+        new_load.to_bits() as u64,
+    );
+
+    let mut load = old * exp + new * (one - exp);
+    if new >= old {
+        load += one - 1;
+    }
+
+    FixedPoint::from_bits((load / one) as u32)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn calc_loadavg_converges() {
+        let one = FixedPoint::ONE;
+
+        let mut load = FixedPoint::ZERO;
+        for _ in 0..1000 {
+            load = calc_loadavg(load, EXP_15, one);
+        }
+        assert_eq!(load, one);
+
+        for _ in 0..1000 {
+            load = calc_loadavg(load, EXP_15, FixedPoint::ZERO);
+        }
+        assert_eq!(load, FixedPoint::ZERO);
+    }
 }
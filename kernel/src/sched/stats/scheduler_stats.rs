@@ -18,7 +18,10 @@ pub fn set_stats_from_scheduler(scheduler: &'static dyn SchedulerStats) {
 
     // Register a callback to update the load average periodically
     timer::register_callback(|| {
-        loadavg::update_loadavg(|| nr_queued_and_running().0);
+        loadavg::update_loadavg(|| {
+            let (nr_queued, nr_running) = nr_queued_and_running();
+            nr_queued + nr_running + nr_uninterruptible()
+        });
     });
 }
 
@@ -29,9 +32,17 @@ pub trait SchedulerStats: Sync + Send {
     /// We decided to return a tuple instead of having two separate functions to
     /// avoid the overhead of disabling the preemption twice to inspect the scheduler.
     fn nr_queued_and_running(&self) -> (u32, u32);
+
+    /// Returns the number of tasks in the uninterruptible sleep.
+    fn nr_uninterruptible(&self) -> u32;
 }
 
 /// Get the amount of tasks in the runqueues and the amount of running tasks.
 pub fn nr_queued_and_running() -> (u32, u32) {
     SCHEDULER_STATS.get().unwrap().nr_queued_and_running()
 }
+
+/// Get the amount of tasks in the uninterruptible sleep.
+pub fn nr_uninterruptible() -> u32 {
+    SCHEDULER_STATS.get().unwrap().nr_uninterruptible()
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The hung-task detector.
+//!
+//! A kernel thread periodically scans all POSIX threads. A thread that has
+//! stayed in the uninterruptible sleep for longer than the timeout is reported
+//! together with the location where it blocks and the system call it executes.
+
+use core::time::Duration;
+
+use ostd::{sync::WaitQueue, task::Task, timer::Jiffies};
+
+use super::config;
+use crate::{
+    prelude::*,
+    process::{posix_thread::AsPosixThread, process_table},
+    sched::{Nice, SchedPolicy},
+    thread::{kernel_thread::ThreadOptions, AsThread, Tid},
+};
+
+pub(super) fn init() {
+    ThreadOptions::new(|| HungTaskDetector::new().run())
+        .sched_policy(SchedPolicy::Fair(Nice::MIN))
+        .spawn();
+}
+
+struct HungTaskDetector {
+    timeout: Duration,
+    nr_warnings_left: u64,
+    /// The hangs that have been reported, identified by the TIDs and the
+    /// jiffies when the threads went to sleep, so that each hang is reported once.
+    reported: BTreeSet<(Tid, u64)>,
+}
+
+impl HungTaskDetector {
+    fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(config().hung_task_timeout_secs),
+            nr_warnings_left: config().hung_task_warnings,
+            reported: BTreeSet::new(),
+        }
+    }
+
+    fn run(mut self) {
+        let wait_queue = WaitQueue::new();
+        loop {
+            let _ = wait_queue.wait_until_or_timeout(|| None::<()>, &self.timeout);
+            self.check();
+        }
+    }
+
+    fn check(&mut self) {
+        let now = Jiffies::elapsed();
+        let processes: Vec<_> = process_table::process_table_mut().iter().cloned().collect();
+
+        let mut sleepers = Vec::new();
+        for process in processes {
+            let tasks = process.tasks().lock();
+            for task in tasks.as_slice() {
+                let Some(sleep_start) = task
+                    .as_thread()
+                    .and_then(|thread| thread.sched_attr().uninterruptible_sleep_start())
+                else {
+                    continue;
+                };
+                let tid = task.as_posix_thread().unwrap().tid();
+                sleepers.push((task.clone(), tid, sleep_start));
+            }
+        }
+
+        for (task, blocked_for) in self.find_new_hangs(now, sleepers) {
+            report_hung_task(&task, blocked_for);
+        }
+    }
+
+    /// Finds the sleepers that have newly become hung, i.e., that have slept
+    /// for longer than the timeout and have not been reported.
+    ///
+    /// Each sleeper is given as a thread, its TID, and the jiffies when it went
+    /// to the uninterruptible sleep. At most `nr_warnings_left` hangs are found.
+    fn find_new_hangs<T>(
+        &mut self,
+        now: Jiffies,
+        sleepers: impl IntoIterator<Item = (T, Tid, Jiffies)>,
+    ) -> Vec<(T, Duration)> {
+        let mut new_hangs = Vec::new();
+        let mut still_hung = BTreeSet::new();
+        for (thread, tid, sleep_start) in sleepers {
+            let blocked_for =
+                Jiffies::new(now.as_u64().saturating_sub(sleep_start.as_u64())).as_duration();
+            if blocked_for < self.timeout {
+                continue;
+            }
+
+            let key = (tid, sleep_start.as_u64());
+            if !self.reported.contains(&key) {
+                if self.nr_warnings_left == 0 {
+                    continue;
+                }
+                self.nr_warnings_left -= 1;
+                new_hangs.push((thread, blocked_for));
+            }
+            still_hung.insert(key);
+        }
+        self.reported = still_hung;
+        new_hangs
+    }
+}
+
+fn report_hung_task(task: &Task, blocked_for: Duration) {
+    let posix_thread = task.as_posix_thread().unwrap();
+
+    let name = posix_thread
+        .thread_name()
+        .lock()
+        .as_ref()
+        .and_then(|name| name.name().ok().flatten())
+        .and_then(|name| name.to_str().ok())
+        .map(String::from)
+        .unwrap_or_default();
+    log::error!(
+        "INFO: task {}:{} blocked for more than {} seconds.",
+        name,
+        posix_thread.tid(),
+        blocked_for.as_secs()
+    );
+    if let Some(location) = task.wait_location() {
+        log::error!("  blocked at {}:{}", location.file(), location.line());
+    }
+    if let Some((num, args)) = posix_thread.syscall_info().read().syscall {
+        log::error!("  in system call {} with arguments {:#x?}", num, args);
+    }
+
+    config().hung_task_policy.apply("hung_task: blocked tasks");
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{arch::timer::TIMER_FREQ, prelude::*};
+
+    use super::*;
+
+    const TIMEOUT_SECS: u64 = 2;
+
+    fn new_detector(nr_warnings: u64) -> HungTaskDetector {
+        HungTaskDetector {
+            timeout: Duration::from_secs(TIMEOUT_SECS),
+            nr_warnings_left: nr_warnings,
+            reported: BTreeSet::new(),
+        }
+    }
+
+    fn secs(secs: u64) -> Jiffies {
+        Jiffies::new(secs * TIMER_FREQ)
+    }
+
+    #[ktest]
+    fn report_once_per_hang() {
+        let mut detector = new_detector(10);
+
+        // Thread 1 has slept for too long, while thread 2 has just slept.
+        let sleepers = [(1, 1, secs(10)), (2, 2, secs(11))];
+        let hangs = detector.find_new_hangs(secs(12), sleepers);
+        assert_eq!(hangs, [(1, Duration::from_secs(2))]);
+
+        // The same hang is not reported again, but a new one is.
+        let hangs = detector.find_new_hangs(secs(14), sleepers);
+        assert_eq!(hangs, [(2, Duration::from_secs(3))]);
+        assert!(detector.find_new_hangs(secs(16), sleepers).is_empty());
+
+        // Thread 1 wakes up and then sleeps again, which is a new hang.
+        let sleepers = [(1, 1, secs(17)), (2, 2, secs(11))];
+        assert!(detector.find_new_hangs(secs(18), sleepers).is_empty());
+        let hangs = detector.find_new_hangs(secs(19), sleepers);
+        assert_eq!(hangs, [(1, Duration::from_secs(2))]);
+    }
+
+    #[ktest]
+    fn limit_warnings() {
+        let mut detector = new_detector(1);
+
+        let sleepers = [(1, 1, secs(0)), (2, 2, secs(0))];
+        let hangs = detector.find_new_hangs(secs(TIMEOUT_SECS), sleepers);
+        assert_eq!(hangs.len(), 1);
+        assert_eq!(detector.nr_warnings_left, 0);
+
+        assert!(detector
+            .find_new_hangs(secs(TIMEOUT_SECS + 1), sleepers)
+            .is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The soft-lockup and hard-lockup detectors.
+//!
+//! Every CPU runs a watchdog thread with the highest real-time priority, which
+//! periodically touches a per-CPU timestamp. The timer interrupt handler of the
+//! CPU checks the timestamp. If it is older than twice the threshold, the CPU
+//! has not scheduled for that long, i.e., there is a soft lockup.
+//!
+//! The timer interrupt handler also counts the timer interrupts. If the count
+//! has not increased for the threshold, the CPU has not handled timer
+//! interrupts for that long, i.e., there is a hard lockup, typically a loop with
+//! local IRQs disabled. The count is checked in one of the following ways:
+//!  - Like Linux, if the CPU has a usable PMU, the watchdog thread starts a
+//!    cycle counter whose overflows raise NMIs on the CPU. The NMI handler
+//!    checks the count, so it reports what the locked CPU is doing.
+//!  - Otherwise, the timer interrupt handler of the next CPU (its buddy) checks
+//!    the count once per threshold (Linux's `CONFIG_HARDLOCKUP_DETECTOR_BUDDY`).
+
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    cpu::{all_cpus, num_cpus, CpuId, PinCurrentCpu},
+    cpu_local,
+    sync::WaitQueue,
+    task::{disable_preempt, Task},
+    timer::{self, Jiffies},
+};
+
+use super::config;
+use crate::{
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    sched::{RealTimePolicy, RealTimePriority, SchedPolicy},
+    thread::kernel_thread::ThreadOptions,
+};
+
+/// The per-CPU state of the lockup detectors.
+struct CpuWatchdog {
+    /// Whether the detectors are running on the CPU.
+    is_enabled: AtomicBool,
+    /// The jiffies when the watchdog thread last ran on the CPU.
+    touch_timestamp: AtomicU64,
+    is_softlockup_reported: AtomicBool,
+    nr_timer_interrupts: AtomicU64,
+    is_hardlockup_reported: AtomicBool,
+    /// The jiffies when the CPU last checked its buddy.
+    buddy_last_check: AtomicU64,
+    /// The number of timer interrupts of the buddy at the last check.
+    buddy_saved_interrupts: AtomicU64,
+    /// Whether the hard lockups of the CPU are detected by NMIs.
+    has_nmi_detector: AtomicBool,
+    /// The number of timer interrupts at the last NMI.
+    #[cfg(target_arch = "x86_64")]
+    nmi_saved_interrupts: AtomicU64,
+    /// The TSC value when the number of timer interrupts last changed.
+    #[cfg(target_arch = "x86_64")]
+    nmi_saved_tsc: AtomicU64,
+    /// The RIP of the hard lockup that is detected by NMIs but not logged yet.
+    ///
+    /// Logging in the NMI context may deadlock, so the hard lockup is logged
+    /// later in the timer interrupt handler of the CPU or its buddy.
+    pending_hardlockup_rip: AtomicUsize,
+}
+
+impl CpuWatchdog {
+    const fn new() -> Self {
+        Self {
+            is_enabled: AtomicBool::new(false),
+            touch_timestamp: AtomicU64::new(0),
+            is_softlockup_reported: AtomicBool::new(false),
+            nr_timer_interrupts: AtomicU64::new(0),
+            is_hardlockup_reported: AtomicBool::new(false),
+            buddy_last_check: AtomicU64::new(0),
+            buddy_saved_interrupts: AtomicU64::new(0),
+            has_nmi_detector: AtomicBool::new(false),
+            #[cfg(target_arch = "x86_64")]
+            nmi_saved_interrupts: AtomicU64::new(0),
+            #[cfg(target_arch = "x86_64")]
+            nmi_saved_tsc: AtomicU64::new(0),
+            pending_hardlockup_rip: AtomicUsize::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        self.touch_timestamp
+            .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+        self.is_softlockup_reported.store(false, Ordering::Relaxed);
+    }
+}
+
+cpu_local! {
+    static WATCHDOG: CpuWatchdog = CpuWatchdog::new();
+}
+
+pub(super) fn init() {
+    for cpu in all_cpus() {
+        ThreadOptions::new(watchdog_thread)
+            .cpu_affinity(cpu.into())
+            .sched_policy(SchedPolicy::RealTime {
+                rt_prio: RealTimePriority::MIN,
+                rt_policy: RealTimePolicy::Fifo,
+            })
+            .spawn();
+    }
+}
+
+/// Returns the hard-lockup threshold in jiffies.
+fn hardlockup_thresh() -> u64 {
+    config().thresh_secs * TIMER_FREQ
+}
+
+/// Returns the soft-lockup threshold in jiffies.
+fn softlockup_thresh() -> u64 {
+    hardlockup_thresh() * 2
+}
+
+fn watchdog_thread() {
+    let preempt_guard = disable_preempt();
+    let cpu = preempt_guard.current_cpu();
+    let watchdog = WATCHDOG.get_on_cpu(cpu);
+
+    watchdog.touch();
+    // The thread is bound to `cpu`, so the callback is registered on `cpu`.
+    timer::register_callback(move || on_timer_interrupt(cpu));
+    #[cfg(target_arch = "x86_64")]
+    let _nmi_detector = nmi_detector::start(watchdog, &preempt_guard);
+    watchdog.is_enabled.store(true, Ordering::Relaxed);
+    drop(preempt_guard);
+
+    // Like Linux, touch the timestamp five times per soft-lockup threshold.
+    let sample_period = Jiffies::new(softlockup_thresh() / 5).as_duration();
+    let wait_queue = WaitQueue::new();
+    loop {
+        let _ = wait_queue.wait_until_or_timeout(|| None::<()>, &sample_period);
+        watchdog.touch();
+    }
+}
+
+fn on_timer_interrupt(cpu: CpuId) {
+    let watchdog = WATCHDOG.get_on_cpu(cpu);
+    let now = Jiffies::elapsed().as_u64();
+
+    watchdog.nr_timer_interrupts.fetch_add(1, Ordering::Relaxed);
+    check_softlockup(cpu, watchdog, now);
+    report_pending_hardlockup(cpu, watchdog);
+    check_buddy(cpu, watchdog, now);
+}
+
+fn check_softlockup(cpu: CpuId, watchdog: &CpuWatchdog, now: u64) {
+    let stuck_for = now.saturating_sub(watchdog.touch_timestamp.load(Ordering::Relaxed));
+    if stuck_for <= softlockup_thresh()
+        || watchdog
+            .is_softlockup_reported
+            .swap(true, Ordering::Relaxed)
+    {
+        return;
+    }
+
+    let current = Task::current().and_then(|task| {
+        task.as_posix_thread()
+            .map(|posix_thread| posix_thread.tid())
+    });
+    log::error!(
+        "BUG: soft lockup - CPU#{} stuck for {}s! [tid: {:?}]",
+        cpu.as_usize(),
+        Jiffies::new(stuck_for).as_duration().as_secs(),
+        current
+    );
+
+    config().softlockup_policy.apply("softlockup: hung tasks");
+}
+
+fn check_buddy(cpu: CpuId, watchdog: &CpuWatchdog, now: u64) {
+    if num_cpus() == 1 {
+        return;
+    }
+    let buddy_cpu = CpuId::try_from((cpu.as_usize() + 1) % num_cpus()).unwrap();
+    let buddy = WATCHDOG.get_on_cpu(buddy_cpu);
+    if !buddy.is_enabled.load(Ordering::Relaxed) {
+        return;
+    }
+    if buddy.has_nmi_detector.load(Ordering::Relaxed) {
+        // The buddy may be stuck, so it cannot log its own hard lockup.
+        report_pending_hardlockup(buddy_cpu, buddy);
+        return;
+    }
+
+    let last_check = watchdog.buddy_last_check.load(Ordering::Relaxed);
+    if now.saturating_sub(last_check) < hardlockup_thresh() {
+        return;
+    }
+    watchdog.buddy_last_check.store(now, Ordering::Relaxed);
+
+    let nr_interrupts = buddy.nr_timer_interrupts.load(Ordering::Relaxed);
+    let saved_interrupts = watchdog
+        .buddy_saved_interrupts
+        .swap(nr_interrupts, Ordering::Relaxed);
+    // A saved count of zero means that this is the first check.
+    if saved_interrupts == 0 || nr_interrupts != saved_interrupts {
+        buddy.is_hardlockup_reported.store(false, Ordering::Relaxed);
+        return;
+    }
+    if buddy.is_hardlockup_reported.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    log::error!(
+        "Watchdog detected hard LOCKUP on CPU#{} (no timer interrupts for {}s)",
+        buddy_cpu.as_usize(),
+        config().thresh_secs
+    );
+
+    config().hardlockup_policy.apply("Hard LOCKUP");
+}
+
+fn report_pending_hardlockup(cpu: CpuId, watchdog: &CpuWatchdog) {
+    let rip = watchdog.pending_hardlockup_rip.swap(0, Ordering::Relaxed);
+    if rip == 0 {
+        return;
+    }
+
+    log::error!(
+        "Watchdog detected hard LOCKUP on CPU#{} (no timer interrupts for {}s) at RIP {:#x}",
+        cpu.as_usize(),
+        config().thresh_secs,
+        rip
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+mod nmi_detector {
+    use core::sync::atomic::Ordering;
+
+    use ostd::{
+        arch::{
+            pmu::{self, PmuEvent, SamplingCounter},
+            read_tsc, tsc_freq,
+        },
+        cpu::{current_cpu_racy, PinCurrentCpu},
+        trap::TrapFrame,
+    };
+
+    use super::{config, CpuWatchdog, Policy, WATCHDOG};
+
+    /// Starts detecting the hard lockups of the current CPU with NMIs.
+    ///
+    /// The detector stops when the returned counter is dropped.
+    pub(super) fn start(
+        watchdog: &CpuWatchdog,
+        guard: &dyn PinCurrentCpu,
+    ) -> Option<SamplingCounter> {
+        pmu::info()?;
+
+        // Like Linux, the period is the threshold in CPU cycles, which is
+        // approximated with the TSC frequency. It may be capped by the PMU, but
+        // the NMI handler compares the TSC values against the threshold anyway.
+        let period = tsc_freq() * config().thresh_secs;
+
+        watchdog.nmi_saved_tsc.store(read_tsc(), Ordering::Relaxed);
+        let counter = SamplingCounter::start(PmuEvent::CoreCycles, period, on_overflow, guard)
+            .inspect_err(|err| {
+                log::warn!(
+                    "[watchdog] failed to start the NMI hard-lockup detector: {:?}",
+                    err
+                )
+            })
+            .ok()?;
+        watchdog.has_nmi_detector.store(true, Ordering::Relaxed);
+
+        Some(counter)
+    }
+
+    /// Checks the hard lockup in the NMI context.
+    fn on_overflow(trap_frame: &TrapFrame) {
+        let cpu = current_cpu_racy();
+        let watchdog = WATCHDOG.get_on_cpu(cpu);
+        let now = read_tsc();
+
+        let nr_interrupts = watchdog.nr_timer_interrupts.load(Ordering::Relaxed);
+        let saved_interrupts = watchdog
+            .nmi_saved_interrupts
+            .swap(nr_interrupts, Ordering::Relaxed);
+        if nr_interrupts != saved_interrupts {
+            watchdog.nmi_saved_tsc.store(now, Ordering::Relaxed);
+            watchdog
+                .is_hardlockup_reported
+                .store(false, Ordering::Relaxed);
+            return;
+        }
+
+        let stuck_since = watchdog.nmi_saved_tsc.load(Ordering::Relaxed);
+        if now.saturating_sub(stuck_since) < tsc_freq() * config().thresh_secs
+            || watchdog
+                .is_hardlockup_reported
+                .swap(true, Ordering::Relaxed)
+        {
+            return;
+        }
+
+        if config().hardlockup_policy == Policy::Panic {
+            panic!(
+                "Watchdog detected hard LOCKUP on CPU#{} at RIP {:#x}",
+                cpu.as_usize(),
+                trap_frame.rip
+            );
+        }
+        watchdog
+            .pending_hardlockup_rip
+            .store(trap_frame.rip, Ordering::Relaxed);
+    }
+}
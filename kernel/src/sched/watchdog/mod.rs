@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel watchdogs.
+//!
+//! There are three watchdogs:
+//!  - The hung-task detector reports POSIX threads that stay in the
+//!    uninterruptible sleep for too long;
+//!  - The soft-lockup detector reports CPUs that do not schedule for too long;
+//!  - The hard-lockup detector reports CPUs that do not handle timer interrupts
+//!    for too long.
+//!
+//! Each watchdog either warns or panics, which is configured with the
+//! following kernel command-line arguments:
+//!
+//! | Argument                          | Default | Meaning                                           |
+//! |-----------------------------------|---------|---------------------------------------------------|
+//! | `watchdog.thresh`                 | 10      | The hard-lockup threshold in seconds; the         |
+//! |                                   |         | soft-lockup threshold is twice the value; 0       |
+//! |                                   |         | disables the lockup detectors                     |
+//! | `watchdog.softlockup_panic`       | 0       | Panics on soft lockups                            |
+//! | `watchdog.hardlockup_panic`       | 0       | Panics on hard lockups                            |
+//! | `watchdog.hung_task_timeout_secs` | 120     | The hung-task threshold; 0 disables the detector  |
+//! | `watchdog.hung_task_panic`        | 0       | Panics on hung tasks                              |
+//! | `watchdog.hung_task_warnings`     | 10      | The maximum number of reported hung tasks         |
+
+use ostd::boot::boot_info;
+use spin::Once;
+
+use crate::{
+    kcmdline::{KCmdlineArg, ModuleArg},
+    prelude::*,
+};
+
+mod hung_task;
+mod lockup;
+
+/// What a watchdog does when it detects a problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Policy {
+    Warn,
+    Panic,
+}
+
+impl Policy {
+    fn from_flag(flag: u64) -> Self {
+        if flag == 0 {
+            Self::Warn
+        } else {
+            Self::Panic
+        }
+    }
+
+    /// Panics with `reason` if the policy requires so.
+    ///
+    /// The details of the problem should have been logged before.
+    fn apply(self, reason: &str) {
+        if self == Self::Panic {
+            panic!("{}", reason);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchdogConfig {
+    thresh_secs: u64,
+    softlockup_policy: Policy,
+    hardlockup_policy: Policy,
+    hung_task_timeout_secs: u64,
+    hung_task_policy: Policy,
+    hung_task_warnings: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            thresh_secs: 10,
+            softlockup_policy: Policy::Warn,
+            hardlockup_policy: Policy::Warn,
+            hung_task_timeout_secs: 120,
+            hung_task_policy: Policy::Warn,
+            hung_task_warnings: 10,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    fn from_kcmdline() -> Self {
+        let mut config = Self::default();
+
+        let karg = KCmdlineArg::from(boot_info().kernel_cmdline.as_str());
+        let Some(args) = karg.get_module_args("watchdog") else {
+            return config;
+        };
+
+        for arg in args {
+            let ModuleArg::KeyVal(key, value) = arg else {
+                continue;
+            };
+            let Some(value) = parse_u64(value) else {
+                log::warn!("[watchdog] invalid value for {:?}", key);
+                continue;
+            };
+            match key.to_bytes() {
+                b"thresh" => config.thresh_secs = value,
+                b"softlockup_panic" => config.softlockup_policy = Policy::from_flag(value),
+                b"hardlockup_panic" => config.hardlockup_policy = Policy::from_flag(value),
+                b"hung_task_timeout_secs" => config.hung_task_timeout_secs = value,
+                b"hung_task_panic" => config.hung_task_policy = Policy::from_flag(value),
+                b"hung_task_warnings" => config.hung_task_warnings = value,
+                _ => log::warn!("[watchdog] unknown argument {:?}", key),
+            }
+        }
+
+        config
+    }
+}
+
+fn parse_u64(value: &CStr) -> Option<u64> {
+    value.to_str().ok()?.parse().ok()
+}
+
+static CONFIG: Once<WatchdogConfig> = Once::new();
+
+fn config() -> &'static WatchdogConfig {
+    CONFIG.get().unwrap()
+}
+
+/// Starts the watchdogs.
+pub fn init() {
+    let config = CONFIG.call_once(WatchdogConfig::from_kcmdline);
+
+    if config.thresh_secs != 0 {
+        lockup::init();
+    }
+    if config.hung_task_timeout_secs != 0 {
+        hung_task::init();
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{setxattr::is_capable, SyscallReturn};
+use crate::{
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    time::{clocks::RealTimeClock, timeval_t, Clock},
+};
+
+/// Reads or adjusts the real time.
+///
+/// Only the adjustments made by `adjtime` (i.e., slewing the real time by a given offset) are
+/// supported. The NTP state of Linux is not maintained, so the clock is always reported as
+/// unsynchronized.
+pub fn sys_adjtimex(timex_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let mut timex = user_space.read_val::<timex_t>(timex_addr)?;
+    let modes = AdjModes::from_bits(timex.modes)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid modes"))?;
+    debug!("modes = {:?}, offset = {}", modes, timex.offset);
+
+    let realtime_clock = RealTimeClock::get();
+    let remaining_usecs = if modes.contains(AdjModes::ADJ_ADJTIME) {
+        if !modes.contains(AdjModes::ADJ_OFFSET_SINGLESHOT) {
+            return_errno_with_message!(Errno::EINVAL, "adjtime requires ADJ_OFFSET_SINGLESHOT");
+        }
+        if modes.contains(AdjModes::ADJ_OFFSET_READONLY) {
+            realtime_clock.remaining_slew() / NSEC_PER_USEC
+        } else {
+            if !is_capable(CapSet::SYS_TIME, ctx) {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "adjusting the clock requires CAP_SYS_TIME"
+                );
+            }
+            let nanos = timex.offset.saturating_mul(NSEC_PER_USEC);
+            realtime_clock.slew_time(nanos) / NSEC_PER_USEC
+        }
+    } else if modes.is_empty() {
+        0
+    } else {
+        if !is_capable(CapSet::SYS_TIME, ctx) {
+            return_errno_with_message!(Errno::EPERM, "adjusting the clock requires CAP_SYS_TIME");
+        }
+        return_errno_with_message!(Errno::EINVAL, "only adjtime adjustments are supported");
+    };
+
+    timex.offset = remaining_usecs;
+    timex.freq = 0;
+    timex.maxerror = NTP_PHASE_LIMIT;
+    timex.esterror = NTP_PHASE_LIMIT;
+    timex.status = STA_UNSYNC;
+    timex.constant = 2;
+    timex.precision = 1;
+    timex.tolerance = MAXFREQ_SCALED;
+    timex.time = timeval_t::from(realtime_clock.read_time());
+    timex.tick = USEC_PER_TICK;
+    user_space.write_val(timex_addr, &timex)?;
+
+    Ok(SyscallReturn::Return(TIME_ERROR))
+}
+
+const NSEC_PER_USEC: i64 = 1000;
+
+/// The maximum error in microseconds, which is reported if the clock is unsynchronized.
+const NTP_PHASE_LIMIT: i64 = 16_000_000;
+/// The maximum frequency error, i.e., 500 parts per million, in the scaled format of `timex`.
+const MAXFREQ_SCALED: i64 = 500 << 16;
+/// The microseconds between two clock ticks that are reported to the user space.
+const USEC_PER_TICK: i64 = 10_000;
+
+/// The status that indicates that the clock is unsynchronized.
+const STA_UNSYNC: i32 = 0x0040;
+/// The clock state that indicates that the clock is unsynchronized.
+const TIME_ERROR: isize = 5;
+
+bitflags! {
+    struct AdjModes: u32 {
+        const ADJ_OFFSET            = 0x0001;
+        const ADJ_FREQUENCY         = 0x0002;
+        const ADJ_MAXERROR          = 0x0004;
+        const ADJ_ESTERROR          = 0x0008;
+        const ADJ_STATUS            = 0x0010;
+        const ADJ_TIMECONST         = 0x0020;
+        const ADJ_TAI               = 0x0080;
+        const ADJ_SETOFFSET         = 0x0100;
+        const ADJ_MICRO             = 0x1000;
+        const ADJ_NANO              = 0x2000;
+        const ADJ_TICK              = 0x4000;
+        const ADJ_ADJTIME           = 0x8000;
+
+        const ADJ_OFFSET_SINGLESHOT = 0x0001;
+        const ADJ_OFFSET_READONLY   = 0x2000;
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct timex_t {
+    modes: u32,
+    _pad0: u32,
+    offset: i64,
+    freq: i64,
+    maxerror: i64,
+    esterror: i64,
+    status: i32,
+    _pad1: u32,
+    constant: i64,
+    precision: i64,
+    tolerance: i64,
+    time: timeval_t,
+    tick: i64,
+    ppsfreq: i64,
+    jitter: i64,
+    shift: i32,
+    _pad2: u32,
+    stabil: i64,
+    jitcnt: i64,
+    calcnt: i64,
+    errcnt: i64,
+    stbcnt: i64,
+    tai: i32,
+    _reserved: [i32; 11],
+}
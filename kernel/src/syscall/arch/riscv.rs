@@ -3,6 +3,7 @@
 use crate::syscall::{
     accept::{sys_accept, sys_accept4},
     access::{sys_faccessat, sys_faccessat2},
+    adjtimex::sys_adjtimex,
     bind::sys_bind,
     brk::sys_brk,
     capget::sys_capget,
@@ -11,21 +12,25 @@ use crate::syscall::{
     chmod::{sys_fchmod, sys_fchmodat},
     chown::{sys_fchown, sys_fchownat},
     chroot::sys_chroot,
+    clock_getres::sys_clock_getres,
     clock_gettime::sys_clock_gettime,
+    clock_settime::sys_clock_settime,
     clone::{sys_clone, sys_clone3},
-    close::sys_close,
+    close::{sys_close, sys_close_range},
     connect::sys_connect,
+    copy_file_range::sys_copy_file_range,
     dup::{sys_dup, sys_dup3},
-    epoll::{sys_epoll_create1, sys_epoll_ctl, sys_epoll_pwait},
+    epoll::{sys_epoll_create1, sys_epoll_ctl, sys_epoll_pwait, sys_epoll_pwait2},
     eventfd::sys_eventfd2,
     execve::{sys_execve, sys_execveat},
     exit::sys_exit,
     exit_group::sys_exit_group,
     fallocate::sys_fallocate,
+    fanotify::{sys_fanotify_init, sys_fanotify_mark},
     fcntl::sys_fcntl,
     flock::sys_flock,
     fsync::{sys_fdatasync, sys_fsync},
-    futex::sys_futex,
+    futex::{sys_futex, sys_futex_waitv},
     get_priority::sys_get_priority,
     getcpu::sys_getcpu,
     getcwd::sys_getcwd,
@@ -48,13 +53,17 @@ use crate::syscall::{
     gettid::sys_gettid,
     gettimeofday::sys_gettimeofday,
     getuid::sys_getuid,
+    getxattr::{sys_fgetxattr, sys_getxattr, sys_getxattrat, sys_lgetxattr},
     impl_syscall_nums_and_dispatch_fn,
+    io_uring::{sys_io_uring_enter, sys_io_uring_register, sys_io_uring_setup},
     ioctl::sys_ioctl,
     kill::sys_kill,
     link::sys_linkat,
     listen::sys_listen,
+    listxattr::{sys_flistxattr, sys_listxattr, sys_listxattrat, sys_llistxattr},
     lseek::sys_lseek,
     madvise::sys_madvise,
+    membarrier::sys_membarrier,
     mkdir::sys_mkdirat,
     mknod::sys_mknodat,
     mmap::sys_mmap,
@@ -63,7 +72,7 @@ use crate::syscall::{
     msync::sys_msync,
     munmap::sys_munmap,
     nanosleep::{sys_clock_nanosleep, sys_nanosleep},
-    open::sys_openat,
+    open::{sys_openat, sys_openat2},
     pipe::sys_pipe2,
     prctl::sys_prctl,
     pread64::sys_pread64,
@@ -72,10 +81,13 @@ use crate::syscall::{
     pselect6::sys_pselect6,
     pwrite64::sys_pwrite64,
     pwritev::{sys_pwritev, sys_pwritev2, sys_writev},
+    quotactl::{sys_quotactl, sys_quotactl_fd},
     read::sys_read,
     readlink::sys_readlinkat,
     recvfrom::sys_recvfrom,
+    recvmmsg::sys_recvmmsg,
     recvmsg::sys_recvmsg,
+    removexattr::{sys_fremovexattr, sys_lremovexattr, sys_removexattr, sys_removexattrat},
     rename::sys_renameat,
     rt_sigaction::sys_rt_sigaction,
     rt_sigpending::sys_rt_sigpending,
@@ -87,6 +99,7 @@ use crate::syscall::{
     sched_getattr::sys_sched_getattr,
     sched_getparam::sys_sched_getparam,
     sched_getscheduler::sys_sched_getscheduler,
+    sched_rr_get_interval::sys_sched_rr_get_interval,
     sched_setattr::sys_sched_setattr,
     sched_setparam::sys_sched_setparam,
     sched_setscheduler::sys_sched_setscheduler,
@@ -95,6 +108,7 @@ use crate::syscall::{
     semget::sys_semget,
     semop::{sys_semop, sys_semtimedop},
     sendfile::sys_sendfile,
+    sendmmsg::sys_sendmmsg,
     sendmsg::sys_sendmsg,
     sendto::sys_sendto,
     set_priority::sys_set_priority,
@@ -112,20 +126,24 @@ use crate::syscall::{
     setreuid::sys_setreuid,
     setsid::sys_setsid,
     setsockopt::sys_setsockopt,
+    settimeofday::sys_settimeofday,
     setuid::sys_setuid,
+    setxattr::{sys_fsetxattr, sys_lsetxattr, sys_setxattr, sys_setxattrat},
     shutdown::sys_shutdown,
     sigaltstack::sys_sigaltstack,
     signalfd::sys_signalfd4,
     socket::sys_socket,
     socketpair::sys_socketpair,
+    splice::{sys_splice, sys_tee, sys_vmsplice},
     stat::{sys_fstat, sys_fstatat},
     statfs::{sys_fstatfs, sys_statfs},
     statx::sys_statx,
     symlink::sys_symlinkat,
     sync::sys_sync,
+    sysinfo::sys_sysinfo,
     tgkill::sys_tgkill,
     timer_create::{sys_timer_create, sys_timer_delete},
-    timer_settime::{sys_timer_gettime, sys_timer_settime},
+    timer_settime::{sys_timer_getoverrun, sys_timer_gettime, sys_timer_settime},
     timerfd_create::sys_timerfd_create,
     timerfd_gettime::sys_timerfd_gettime,
     timerfd_settime::sys_timerfd_settime,
@@ -141,6 +159,18 @@ use crate::syscall::{
 };
 
 impl_syscall_nums_and_dispatch_fn! {
+    SYS_SETXATTR = 5             => sys_setxattr(args[..5]);
+    SYS_LSETXATTR = 6            => sys_lsetxattr(args[..5]);
+    SYS_FSETXATTR = 7            => sys_fsetxattr(args[..5]);
+    SYS_GETXATTR = 8             => sys_getxattr(args[..4]);
+    SYS_LGETXATTR = 9            => sys_lgetxattr(args[..4]);
+    SYS_FGETXATTR = 10           => sys_fgetxattr(args[..4]);
+    SYS_LISTXATTR = 11           => sys_listxattr(args[..3]);
+    SYS_LLISTXATTR = 12          => sys_llistxattr(args[..3]);
+    SYS_FLISTXATTR = 13          => sys_flistxattr(args[..3]);
+    SYS_REMOVEXATTR = 14         => sys_removexattr(args[..2]);
+    SYS_LREMOVEXATTR = 15        => sys_lremovexattr(args[..2]);
+    SYS_FREMOVEXATTR = 16        => sys_fremovexattr(args[..2]);
     SYS_GETCWD = 17              => sys_getcwd(args[..2]);
     SYS_EVENTFD2 = 19            => sys_eventfd2(args[..2]);
     SYS_EPOLL_CREATE1 = 20       => sys_epoll_create1(args[..1]);
@@ -175,6 +205,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_OPENAT = 56              => sys_openat(args[..4]);
     SYS_CLOSE = 57               => sys_close(args[..1]);
     SYS_PIPE2 = 59               => sys_pipe2(args[..2]);
+    SYS_QUOTACTL = 60            => sys_quotactl(args[..4]);
     SYS_GETDENTS64 = 61          => sys_getdents64(args[..3]);
     SYS_LSEEK = 62               => sys_lseek(args[..3]);
     SYS_READ = 63                => sys_read(args[..3]);
@@ -188,6 +219,9 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_SENDFILE64 = 71          => sys_sendfile(args[..4]);
     SYS_PSELECT6 = 72            => sys_pselect6(args[..6]);
     SYS_SIGNALFD4 = 74           => sys_signalfd4(args[..4]);
+    SYS_VMSPLICE = 75            => sys_vmsplice(args[..4]);
+    SYS_SPLICE = 76              => sys_splice(args[..6]);
+    SYS_TEE = 77                 => sys_tee(args[..4]);
     SYS_READLINKAT = 78          => sys_readlinkat(args[..4]);
     SYS_NEWFSTATAT = 79          => sys_fstatat(args[..4]);
     SYS_NEWFSTAT = 80            => sys_fstat(args[..2]);
@@ -207,6 +241,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_GETITIMER = 102          => sys_getitimer(args[..2]);
     SYS_SETITIMER = 103          => sys_setitimer(args[..3]);
     SYS_TIMER_CREATE = 107       => sys_timer_create(args[..3]);
+    SYS_TIMER_GETOVERRUN = 109   => sys_timer_getoverrun(args[..1]);
     SYS_TIMER_DELETE = 111       => sys_timer_delete(args[..1]);
     SYS_SCHED_SETPARAM = 118     => sys_sched_setparam(args[..2]);
     SYS_SCHED_SETSCHEDULER = 119 => sys_sched_setscheduler(args[..3]);
@@ -217,6 +252,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_SCHED_YIELD = 124        => sys_sched_yield(args[..0]);
     SYS_SCHED_GET_PRIORITY_MAX = 125 => sys_sched_get_priority_max(args[..1]);
     SYS_SCHED_GET_PRIORITY_MIN = 126 => sys_sched_get_priority_min(args[..1]);
+    SYS_SCHED_RR_GET_INTERVAL = 127 => sys_sched_rr_get_interval(args[..2]);
     SYS_KILL = 129               => sys_kill(args[..2]);
     SYS_TGKILL = 131             => sys_tgkill(args[..3]);
     SYS_SIGALTSTACK = 132        => sys_sigaltstack(args[..2]);
@@ -250,6 +286,8 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_PRCTL = 167              => sys_prctl(args[..5]);
     SYS_GETCPU = 168             => sys_getcpu(args[..3]);
     SYS_GETTIMEOFDAY = 169       => sys_gettimeofday(args[..1]);
+    SYS_SETTIMEOFDAY = 170       => sys_settimeofday(args[..2]);
+    SYS_ADJTIMEX = 171           => sys_adjtimex(args[..1]);
     SYS_GETPID = 172             => sys_getpid(args[..0]);
     SYS_GETPPID = 173            => sys_getppid(args[..0]);
     SYS_GETUID = 174             => sys_getuid(args[..0]);
@@ -257,6 +295,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_GETGID = 176             => sys_getgid(args[..0]);
     SYS_GETEGID = 177            => sys_getegid(args[..0]);
     SYS_GETTID = 178             => sys_gettid(args[..0]);
+    SYS_SYSINFO = 179            => sys_sysinfo(args[..1]);
     SYS_SEMGET = 190             => sys_semget(args[..3]);
     SYS_SEMCTL = 191             => sys_semctl(args[..4]);
     SYS_SEMOP = 193              => sys_semop(args[..3]);
@@ -284,16 +323,24 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_MSYNC = 227              => sys_msync(args[..3]);
     SYS_MADVISE = 233            => sys_madvise(args[..3]);
     SYS_ACCEPT4 = 242            => sys_accept4(args[..4]);
+    SYS_RECVMMSG = 243           => sys_recvmmsg(args[..5]);
     SYS_WAIT4 = 260              => sys_wait4(args[..4]);
     SYS_PRLIMIT64 = 261          => sys_prlimit64(args[..4]);
+    SYS_FANOTIFY_INIT = 262      => sys_fanotify_init(args[..2]);
+    SYS_FANOTIFY_MARK = 263      => sys_fanotify_mark(args[..5]);
+    SYS_SENDMMSG = 269           => sys_sendmmsg(args[..4]);
     SYS_SCHED_SETATTR = 274      => sys_sched_setattr(args[..3]);
     SYS_SCHED_GETATTR = 275      => sys_sched_getattr(args[..4]);
     SYS_GETRANDOM = 278          => sys_getrandom(args[..3]);
     SYS_EXECVEAT = 281           => sys_execveat(args[..5], &mut user_ctx);
+    SYS_MEMBARRIER = 283         => sys_membarrier(args[..3]);
+    SYS_COPY_FILE_RANGE = 285    => sys_copy_file_range(args[..6]);
     SYS_PREADV2 = 286            => sys_preadv2(args[..5]);
     SYS_PWRITEV2 = 287           => sys_pwritev2(args[..5]);
     SYS_STATX = 291              => sys_statx(args[..5]);
     SYS_CLOCK_GETTIME = 403      => sys_clock_gettime(args[..2]);
+    SYS_CLOCK_SETTIME = 404      => sys_clock_settime(args[..2]);
+    SYS_CLOCK_GETRES = 406       => sys_clock_getres(args[..2]);
     SYS_CLOCK_NANOSLEEP = 407    => sys_clock_nanosleep(args[..4]);
     SYS_TIMER_GETTIME = 408      => sys_timer_gettime(args[..2]);
     SYS_TIMER_SETTIME = 409      => sys_timer_settime(args[..4]);
@@ -301,6 +348,18 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_TIMERFD_SETTIME = 411    => sys_timerfd_settime(args[..4]);
     SYS_UTIMENSAT = 412          => sys_utimensat(args[..4]);
     SYS_SEMTIMEDOP = 420         => sys_semtimedop(args[..4]);
+    SYS_IO_URING_SETUP = 425     => sys_io_uring_setup(args[..2]);
+    SYS_IO_URING_ENTER = 426     => sys_io_uring_enter(args[..6]);
+    SYS_IO_URING_REGISTER = 427  => sys_io_uring_register(args[..4]);
     SYS_CLONE3 = 435             => sys_clone3(args[..2], &user_ctx);
+    SYS_CLOSE_RANGE = 436        => sys_close_range(args[..3]);
+    SYS_OPENAT2 = 437            => sys_openat2(args[..4]);
     SYS_FACCESSAT2 = 439         => sys_faccessat2(args[..4]);
+    SYS_EPOLL_PWAIT2 = 441       => sys_epoll_pwait2(args[..6]);
+    SYS_QUOTACTL_FD = 443        => sys_quotactl_fd(args[..4]);
+    SYS_FUTEX_WAITV = 449        => sys_futex_waitv(args[..5]);
+    SYS_SETXATTRAT = 463         => sys_setxattrat(args[..6]);
+    SYS_GETXATTRAT = 464         => sys_getxattrat(args[..6]);
+    SYS_LISTXATTRAT = 465        => sys_listxattrat(args[..5]);
+    SYS_REMOVEXATTRAT = 466      => sys_removexattrat(args[..4]);
 }
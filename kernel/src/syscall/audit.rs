@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Runtime-configurable syscall auditing.
+//!
+//! Independent of seccomp, a privileged user can deny or log selected syscalls at runtime:
+//!
+//! - `/proc/sys/kernel/syscall_deny` lists the syscalls denied for all processes;
+//! - `/proc/sys/kernel/syscall_log` lists the syscalls logged for all processes;
+//! - `/proc/[pid]/syscall_deny` lists the syscalls denied for a process. The list is inherited
+//!   by the children of the process and is preserved across `execve`.
+//!
+//! A denied syscall fails with `ENOSYS` without being executed, as if the kernel did not
+//! implement it. A logged syscall is printed to the console together with its arguments and
+//! whether it is denied.
+
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{context::Context, prelude::*};
+
+/// The upper bound of the syscall numbers that can be audited.
+///
+/// The syscall numbers on all supported architectures are below this value.
+pub const MAX_SYSCALL_NUM: usize = 512;
+
+const NR_WORDS: usize = MAX_SYSCALL_NUM / u64::BITS as usize;
+
+/// The syscalls denied for all processes.
+static GLOBAL_DENY_LIST: SyscallSet = SyscallSet::new();
+
+/// The syscalls logged for all processes.
+static GLOBAL_LOG_LIST: SyscallSet = SyscallSet::new();
+
+/// Returns the syscalls denied for all processes.
+pub fn global_deny_list() -> &'static SyscallSet {
+    &GLOBAL_DENY_LIST
+}
+
+/// Returns the syscalls logged for all processes.
+pub fn global_log_list() -> &'static SyscallSet {
+    &GLOBAL_LOG_LIST
+}
+
+/// Audits a syscall before it is dispatched.
+///
+/// This method returns an error if the syscall is denied for the current process.
+pub(super) fn audit_syscall(syscall_number: u64, args: &[u64; 6], ctx: &Context) -> Result<()> {
+    let is_denied = GLOBAL_DENY_LIST.contains(syscall_number)
+        || ctx.process.syscall_deny_list().contains(syscall_number);
+
+    if GLOBAL_LOG_LIST.contains(syscall_number) {
+        println!(
+            "[audit] pid={} tid={} syscall={} args={:x?}{}",
+            ctx.process.pid(),
+            ctx.posix_thread.tid(),
+            syscall_number,
+            args,
+            if is_denied { " denied" } else { "" }
+        );
+    }
+
+    if is_denied {
+        return_errno_with_message!(Errno::ENOSYS, "the syscall is denied");
+    }
+
+    Ok(())
+}
+
+/// A set of syscall numbers.
+///
+/// Querying the set takes no locks, so it is cheap to check the set on every syscall.
+pub struct SyscallSet {
+    bits: [AtomicU64; NR_WORDS],
+}
+
+impl SyscallSet {
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self {
+            bits: [const { AtomicU64::new(0) }; NR_WORDS],
+        }
+    }
+
+    /// Returns whether the set contains the syscall.
+    pub fn contains(&self, syscall_number: u64) -> bool {
+        if syscall_number >= MAX_SYSCALL_NUM as u64 {
+            return false;
+        }
+
+        let (word, bit) = Self::position(syscall_number as usize);
+        self.bits[word].load(Ordering::Relaxed) & bit != 0
+    }
+
+    /// Replaces the syscalls in the set with those in `other`.
+    pub fn copy_from(&self, other: &SyscallSet) {
+        for (dst, src) in self.bits.iter().zip(other.bits.iter()) {
+            dst.store(src.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Formats the syscalls in the set as numbers separated by spaces.
+    pub fn format(&self) -> String {
+        let mut output = String::new();
+        for syscall_number in 0..MAX_SYSCALL_NUM {
+            if !self.contains(syscall_number as u64) {
+                continue;
+            }
+            if !output.is_empty() {
+                output.push(' ');
+            }
+            write!(output, "{}", syscall_number).unwrap();
+        }
+        output.push('\n');
+        output
+    }
+
+    /// Updates the set with a command.
+    ///
+    /// The command consists of tokens separated by whitespace:
+    /// - `+N` adds the syscall `N` to the set;
+    /// - `-N` removes the syscall `N` from the set;
+    /// - `N` also adds the syscall `N`, but the set is cleared first if any such token exists.
+    ///
+    /// So writing a plain list of numbers replaces the set, and writing an empty command
+    /// clears the set. The set is left unchanged if the command is invalid.
+    pub fn update(&self, command: &str) -> Result<()> {
+        let mut tokens = command.split_whitespace().peekable();
+        let is_replaced = tokens.peek().is_none()
+            || command
+                .split_whitespace()
+                .any(|token| !token.starts_with('+') && !token.starts_with('-'));
+
+        let mut to_add = [0u64; NR_WORDS];
+        let mut to_remove = [0u64; NR_WORDS];
+
+        for token in tokens {
+            let (number, is_added) = if let Some(number) = token.strip_prefix('+') {
+                (number, true)
+            } else if let Some(number) = token.strip_prefix('-') {
+                (number, false)
+            } else {
+                (token, true)
+            };
+
+            let syscall_number = number
+                .parse::<usize>()
+                .ok()
+                .filter(|num| *num < MAX_SYSCALL_NUM)
+                .ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "the syscall number is invalid")
+                })?;
+            let (word, bit) = Self::position(syscall_number);
+            if is_added {
+                to_add[word] |= bit;
+                to_remove[word] &= !bit;
+            } else {
+                to_remove[word] |= bit;
+                to_add[word] &= !bit;
+            }
+        }
+
+        for (word, bits) in self.bits.iter().enumerate() {
+            if is_replaced {
+                bits.store(to_add[word], Ordering::Relaxed);
+            } else {
+                bits.fetch_or(to_add[word], Ordering::Relaxed);
+                bits.fetch_and(!to_remove[word], Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn position(syscall_number: usize) -> (usize, u64) {
+        let word = syscall_number / u64::BITS as usize;
+        let bit = 1 << (syscall_number % u64::BITS as usize);
+        (word, bit)
+    }
+}
+
+impl Default for SyscallSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use ostd::arch::timer::TIMER_FREQ;
+
+use super::{
+    clock_gettime::{read_clock, ClockId},
+    SyscallReturn,
+};
+use crate::{
+    prelude::*,
+    time::{clockid_t, timespec_t},
+};
+
+pub fn sys_clock_getres(
+    clockid: clockid_t,
+    res_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!("clockid = {:?}, res_addr = {:#x}", clockid, res_addr);
+
+    // Check whether the clock exists.
+    read_clock(clockid, ctx)?;
+
+    // The coarse clocks are updated on timer interrupts, while the others have the resolution of
+    // nanoseconds, which is also reported by the VDSO.
+    let res = match ClockId::try_from(clockid) {
+        Ok(ClockId::CLOCK_REALTIME_COARSE | ClockId::CLOCK_MONOTONIC_COARSE) => {
+            Duration::from_nanos(1_000_000_000 / TIMER_FREQ)
+        }
+        _ => Duration::from_nanos(1),
+    };
+
+    if res_addr != 0 {
+        ctx.user_space()
+            .write_val(res_addr, &timespec_t::from(res))?;
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
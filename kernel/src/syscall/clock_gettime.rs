@@ -6,6 +6,8 @@ use int_to_c_enum::TryFromInt;
 
 use super::SyscallReturn;
 use crate::{
+    device::ptp,
+    fs::file_table::{get_file_fast, FileDesc},
     prelude::*,
     process::{
         posix_thread::{thread_table, AsPosixThread},
@@ -68,7 +70,6 @@ pub enum ClockId {
 pub enum DynamicClockIdInfo {
     Pid(u32, DynamicClockType),
     Tid(u32, DynamicClockType),
-    #[expect(dead_code)]
     Fd(u32),
 }
 
@@ -149,7 +150,16 @@ pub fn read_clock(clockid: clockid_t, ctx: &Context) -> Result<Duration> {
                     _ => unimplemented!(),
                 }
             }
-            DynamicClockIdInfo::Fd(_) => unimplemented!(),
+            DynamicClockIdInfo::Fd(fd) => {
+                let file = {
+                    let mut file_table = ctx.thread_local.borrow_file_table_mut();
+                    get_file_fast!(&mut file_table, fd as FileDesc).into_owned()
+                };
+                let Some(clock) = ptp::get_clock(file.as_ref()) else {
+                    return_errno_with_message!(Errno::EINVAL, "the file is not a clock");
+                };
+                clock.read_time()
+            }
         }
     }
 }
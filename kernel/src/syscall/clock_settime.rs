@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{clock_gettime::ClockId, setxattr::is_capable, SyscallReturn};
+use crate::{
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    time::{clockid_t, clocks::RealTimeClock, timespec_t},
+};
+
+pub fn sys_clock_settime(
+    clockid: clockid_t,
+    timespec_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let timespec = ctx.user_space().read_val::<timespec_t>(timespec_addr)?;
+    debug!("clockid = {:?}, timespec = {:?}", clockid, timespec);
+
+    let time = Duration::try_from(timespec)?;
+    // Like Linux, only the real time can be set.
+    if !matches!(ClockId::try_from(clockid), Ok(ClockId::CLOCK_REALTIME)) {
+        return_errno_with_message!(Errno::EINVAL, "the clock cannot be set");
+    }
+    if !is_capable(CapSet::SYS_TIME, ctx) {
+        return_errno_with_message!(Errno::EPERM, "setting the clock requires CAP_SYS_TIME");
+    }
+
+    RealTimeClock::get().set_time(time);
+
+    Ok(SyscallReturn::Return(0))
+}
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use ostd::sync::RwArc;
+
 use super::SyscallReturn;
 use crate::{fs::file_table::FileDesc, prelude::*};
 
@@ -29,3 +31,46 @@ pub fn sys_close(fd: FileDesc, ctx: &Context) -> Result<SyscallReturn> {
     // <https://man7.org/linux/man-pages/man2/close.2.html>.
     Ok(SyscallReturn::Return(0))
 }
+
+pub fn sys_close_range(first: u32, last: u32, flags: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = CloseRangeFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid close_range flags"))?;
+    debug!("first = {}, last = {}, flags = {:?}", first, last, flags);
+
+    if first > last {
+        return_errno_with_message!(Errno::EINVAL, "the first fd is greater than the last fd");
+    }
+
+    let mut file_table_ref = ctx.thread_local.borrow_file_table_mut();
+
+    if flags.contains(CloseRangeFlags::UNSHARE) && file_table_ref.unwrap().get().is_none() {
+        // The file table is shared with other threads or processes. Make a private copy so that
+        // the file descriptors are only closed for the current thread.
+        let new_file_table = RwArc::new(file_table_ref.unwrap().read().clone());
+        *ctx.posix_thread.file_table().lock() = Some(new_file_table.clone_ro());
+        file_table_ref.replace(new_file_table);
+    }
+    let file_table = file_table_ref.unwrap();
+
+    let (first, last) = (first as usize, last as usize);
+    let closed_files = if flags.contains(CloseRangeFlags::CLOEXEC) {
+        file_table.write().set_cloexec_range(first, last);
+        Vec::new()
+    } else {
+        file_table.write().close_range(first, last)
+    };
+
+    // The files are dropped after the lock is released, since dropping a file may block.
+    drop(closed_files);
+
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct CloseRangeFlags: u32 {
+        /// Unshare the file table before closing the file descriptors.
+        const UNSHARE = 1 << 1;
+        /// Set the close-on-exec flag instead of closing the file descriptors.
+        const CLOEXEC = 1 << 2;
+    }
+}
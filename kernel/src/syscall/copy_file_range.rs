@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    splice::{get_files, read_offset, write_offset, MAX_RW_COUNT},
+    SyscallReturn,
+};
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::FileDesc,
+        inode_handle::InodeHandle,
+        utils::{InodeType, SeekFrom, StatusFlags},
+    },
+    prelude::*,
+    process::ResourceType,
+};
+
+pub fn sys_copy_file_range(
+    fd_in: FileDesc,
+    off_in_ptr: Vaddr,
+    fd_out: FileDesc,
+    off_out_ptr: Vaddr,
+    len: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "fd_in = {}, off_in_ptr = 0x{:x}, fd_out = {}, off_out_ptr = 0x{:x}, len = 0x{:x}, flags = {}",
+        fd_in, off_in_ptr, fd_out, off_out_ptr, len, flags
+    );
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the flags must be zero");
+    }
+
+    let (file_in, file_out) = get_files(fd_in, fd_out, ctx)?;
+    if !file_in.access_mode().is_readable() {
+        return_errno_with_message!(Errno::EBADF, "the input file is not readable");
+    }
+    if !file_out.access_mode().is_writable() {
+        return_errno_with_message!(Errno::EBADF, "the output file is not writable");
+    }
+    if file_out.status_flags().contains(StatusFlags::O_APPEND) {
+        return_errno_with_message!(Errno::EBADF, "the output file is opened with O_APPEND");
+    }
+
+    let handle_in = as_regular_file(&*file_in)?;
+    let handle_out = as_regular_file(&*file_out)?;
+    let inode_in = handle_in.dentry().inode();
+    let inode_out = handle_out.dentry().inode();
+
+    let mut off_in = read_offset(off_in_ptr, ctx)?;
+    let mut off_out = read_offset(off_out_ptr, ctx)?;
+    let pos_in = off_in.unwrap_or_else(|| handle_in.offset());
+    let pos_out = off_out.unwrap_or_else(|| handle_out.offset());
+
+    // The offsets are not negative, so the additions below cannot overflow.
+    let mut len = len.min(MAX_RW_COUNT);
+    if pos_in + len > isize::MAX as usize || pos_out + len > isize::MAX as usize {
+        return_errno_with_message!(Errno::EOVERFLOW, "the offset plus the length overflows");
+    }
+    if Arc::ptr_eq(inode_in, inode_out) && pos_in < pos_out + len && pos_out < pos_in + len {
+        return_errno_with_message!(Errno::EINVAL, "the source and the destination overlap");
+    }
+
+    let max_file_size = ctx
+        .process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_FSIZE)
+        .get_cur() as usize;
+    if len > 0 && pos_out >= max_file_size {
+        return_errno_with_message!(Errno::EFBIG, "the offset exceeds the maximum file size");
+    }
+    len = len.min(max_file_size - pos_out);
+
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    // Only file systems that implement `copy_range_from` can copy across file systems.
+    let copied_len = if Arc::ptr_eq(&inode_in.fs(), &inode_out.fs()) {
+        inode_out.copy_from(pos_out, inode_in.as_ref(), pos_in, len)?
+    } else {
+        match inode_out.copy_range_from(pos_out, inode_in.as_ref(), pos_in, len) {
+            Err(err) if err.error() == Errno::EOPNOTSUPP => {
+                return_errno_with_message!(
+                    Errno::EXDEV,
+                    "the files are not on the same file system"
+                )
+            }
+            res => res?,
+        }
+    };
+
+    match off_in.as_mut() {
+        Some(off_in) => *off_in += copied_len,
+        None => {
+            handle_in.seek(SeekFrom::Current(copied_len as isize))?;
+        }
+    }
+    match off_out.as_mut() {
+        Some(off_out) => *off_out += copied_len,
+        None => {
+            handle_out.seek(SeekFrom::Current(copied_len as isize))?;
+        }
+    }
+    write_offset(off_in_ptr, off_in, ctx)?;
+    write_offset(off_out_ptr, off_out, ctx)?;
+
+    Ok(SyscallReturn::Return(copied_len as _))
+}
+
+fn as_regular_file(file: &dyn FileLike) -> Result<&InodeHandle> {
+    let Some(handle) = file.downcast_ref::<InodeHandle>() else {
+        return_errno_with_message!(Errno::EINVAL, "the file is not a regular file");
+    };
+
+    match handle.dentry().inode().type_() {
+        InodeType::File => Ok(handle),
+        InodeType::Dir => return_errno_with_message!(Errno::EISDIR, "the file is a directory"),
+        _ => return_errno_with_message!(Errno::EINVAL, "the file is not a regular file"),
+    }
+}
@@ -12,6 +12,7 @@ use crate::{
     },
     prelude::*,
     process::signal::sig_mask::SigMask,
+    time::timespec_t,
 };
 
 // See: https://elixir.bootlin.com/linux/v6.11.5/source/fs/eventpoll.c#L2437
@@ -95,7 +96,7 @@ pub fn sys_epoll_ctl(
 fn do_epoll_wait(
     epfd: FileDesc,
     max_events: i32,
-    timeout: i32,
+    timeout: Option<Duration>,
     ctx: &Context,
 ) -> Result<Vec<EpollEvent>> {
     let max_events = {
@@ -104,11 +105,6 @@ fn do_epoll_wait(
         }
         max_events as usize
     };
-    let timeout = if timeout >= 0 {
-        Some(Duration::from_millis(timeout as _))
-    } else {
-        None
-    };
 
     let mut file_table = ctx.thread_local.borrow_file_table_mut();
     let file = get_file_fast!(&mut file_table, epfd);
@@ -131,6 +127,30 @@ fn do_epoll_wait(
     result
 }
 
+fn timeout_from_millis(timeout: i32) -> Option<Duration> {
+    if timeout >= 0 {
+        Some(Duration::from_millis(timeout as _))
+    } else {
+        None
+    }
+}
+
+fn write_epoll_events(
+    events_addr: Vaddr,
+    epoll_events: &[EpollEvent],
+    ctx: &Context,
+) -> Result<()> {
+    let mut write_addr = events_addr;
+    let user_space = ctx.user_space();
+    for epoll_event in epoll_events.iter() {
+        let c_epoll_event = c_epoll_event::from(epoll_event);
+        user_space.write_val(write_addr, &c_epoll_event)?;
+        write_addr += core::mem::size_of::<c_epoll_event>();
+    }
+
+    Ok(())
+}
+
 pub fn sys_epoll_wait(
     epfd: FileDesc,
     events_addr: Vaddr,
@@ -143,16 +163,8 @@ pub fn sys_epoll_wait(
         epfd, events_addr, max_events, timeout
     );
 
-    let epoll_events = do_epoll_wait(epfd, max_events, timeout, ctx)?;
-
-    // Write back
-    let mut write_addr = events_addr;
-    let user_space = ctx.user_space();
-    for epoll_event in epoll_events.iter() {
-        let c_epoll_event = c_epoll_event::from(epoll_event);
-        user_space.write_val(write_addr, &c_epoll_event)?;
-        write_addr += core::mem::size_of::<c_epoll_event>();
-    }
+    let epoll_events = do_epoll_wait(epfd, max_events, timeout_from_millis(timeout), ctx)?;
+    write_epoll_events(events_addr, &epoll_events, ctx)?;
 
     Ok(SyscallReturn::Return(epoll_events.len() as _))
 }
@@ -195,6 +207,58 @@ pub fn sys_epoll_pwait(
         epfd, events_addr, max_events, timeout, sigmask, sigset_size
     );
 
+    do_epoll_pwait(
+        epfd,
+        events_addr,
+        max_events,
+        timeout_from_millis(timeout),
+        sigmask,
+        sigset_size,
+        ctx,
+    )
+}
+
+pub fn sys_epoll_pwait2(
+    epfd: FileDesc,
+    events_addr: Vaddr,
+    max_events: i32,
+    timeout_addr: Vaddr,
+    sigmask: Vaddr,
+    sigset_size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "epfd = {}, events_addr = 0x{:x}, max_events = {}, timeout_addr = 0x{:x}, sigmask = 0x{:x}, sigset_size = {}",
+        epfd, events_addr, max_events, timeout_addr, sigmask, sigset_size
+    );
+
+    let timeout = if timeout_addr != 0 {
+        let timespec = ctx.user_space().read_val::<timespec_t>(timeout_addr)?;
+        Some(Duration::try_from(timespec)?)
+    } else {
+        None
+    };
+
+    do_epoll_pwait(
+        epfd,
+        events_addr,
+        max_events,
+        timeout,
+        sigmask,
+        sigset_size,
+        ctx,
+    )
+}
+
+fn do_epoll_pwait(
+    epfd: FileDesc,
+    events_addr: Vaddr,
+    max_events: i32,
+    timeout: Option<Duration>,
+    sigmask: Vaddr,
+    sigset_size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
     if sigmask != 0 && sigset_size != 8 {
         return_errno_with_message!(Errno::EINVAL, "sigset size is not equal to 8");
     }
@@ -213,14 +277,7 @@ pub fn sys_epoll_pwait(
         }
     };
 
-    // Write back
-    let mut write_addr = events_addr;
-    let user_space = ctx.user_space();
-    for event in ready_events.iter() {
-        let c_event = c_epoll_event::from(event);
-        user_space.write_val(write_addr, &c_event)?;
-        write_addr += core::mem::size_of::<c_epoll_event>();
-    }
+    write_epoll_events(events_addr, &ready_events, ctx)?;
 
     Ok(SyscallReturn::Return(ready_events.len() as _))
 }
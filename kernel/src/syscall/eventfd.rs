@@ -74,7 +74,7 @@ bitflags! {
     }
 }
 
-struct EventFile {
+pub struct EventFile {
     counter: Mutex<u64>,
     pollee: Pollee,
     flags: Mutex<Flags>,
@@ -143,6 +143,13 @@ impl EventFile {
         Ok(())
     }
 
+    /// Adds one to the counter without blocking, like Linux's `eventfd_signal`.
+    ///
+    /// If the counter cannot be increased, the counter is left unchanged.
+    pub fn signal(&self) {
+        let _ = self.add_counter_val(1);
+    }
+
     /// Adds val to the counter.
     ///
     /// If the new_value is overflowed or exceeds MAX_COUNTER_VALUE, the counter value
@@ -13,6 +13,7 @@ use crate::{
         fs_resolver::{FsPath, AT_FDCWD},
         path::Dentry,
     },
+    net::socket::netlink::proc_events,
     prelude::*,
     process::{
         check_executable_file, posix_thread::ThreadName, renew_vm_and_map, Credentials, Process,
@@ -145,10 +146,14 @@ fn do_execve(
     *thread_local.robust_list().borrow_mut() = None;
     debug!("load elf in execve succeeds");
 
+    // The new program is dumpable unless it gains privileges below.
+    process.set_dumpable(true);
+
     let credentials = posix_thread.credentials_mut();
     set_uid_from_elf(process, &credentials, &elf_file)?;
     set_gid_from_elf(process, &credentials, &elf_file)?;
     credentials.set_keep_capabilities(false);
+    process.update_vdso_identity(&posix_thread.credentials());
 
     // set executable path
     process.set_executable_path(new_executable_path);
@@ -169,6 +174,9 @@ fn do_execve(
     // set new user stack top
     user_context.set_stack_pointer(elf_load_info.user_stack_top() as _);
     debug!("user stack top: 0x{:x}", elf_load_info.user_stack_top());
+
+    proc_events::on_exec(posix_thread.tid(), process.pid());
+
     Ok(())
 }
 
@@ -228,6 +236,7 @@ fn set_uid_from_elf(
         credentials.set_euid(uid);
 
         current.clear_parent_death_signal();
+        current.set_dumpable(false);
     }
 
     // No matter whether the elf_file has `set_uid` bit, suid should be reset.
@@ -246,6 +255,7 @@ fn set_gid_from_elf(
         credentials.set_egid(gid);
 
         current.clear_parent_death_signal();
+        current.set_dumpable(false);
     }
 
     // No matter whether the the elf file has `set_gid` bit, sgid should be reset.
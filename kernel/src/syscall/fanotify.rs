@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{constants::MAX_FILENAME_LEN, setxattr::is_capable, SyscallReturn};
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FdFlags, FileDesc},
+        fs_resolver::FsPath,
+        notify::{
+            fanotify::{FanotifyFile, FanotifyInitFlags},
+            FsnotifyEvents, FsnotifyObject,
+        },
+        utils::InodeType,
+    },
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+};
+
+pub fn sys_fanotify_init(flags: u32, event_f_flags: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = FanotifyInitFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!("flags = {:?}, event_f_flags = {:#x}", flags, event_f_flags);
+
+    if flags
+        .contains(FanotifyInitFlags::FAN_CLASS_CONTENT | FanotifyInitFlags::FAN_CLASS_PRE_CONTENT)
+    {
+        return_errno_with_message!(Errno::EINVAL, "the notification class is invalid");
+    }
+    if !is_capable(CapSet::SYS_ADMIN, ctx) {
+        return_errno_with_message!(Errno::EPERM, "fanotify requires CAP_SYS_ADMIN");
+    }
+
+    let fanotify_file = FanotifyFile::new(flags, event_f_flags)?;
+    let fd_flags = if flags.contains(FanotifyInitFlags::FAN_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = {
+        let file_table = ctx.thread_local.borrow_file_table();
+        let mut file_table_locked = file_table.unwrap().write();
+        file_table_locked.insert(Arc::new(fanotify_file), fd_flags)
+    };
+
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_fanotify_mark(
+    fanotify_fd: FileDesc,
+    flags: u32,
+    mask: u64,
+    dirfd: FileDesc,
+    path_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = MarkFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    let events = FsnotifyEvents::from_bits(mask)
+        .filter(|events| !events.contains(FsnotifyEvents::Q_OVERFLOW))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid mask"))?;
+    debug!(
+        "fanotify_fd = {}, flags = {:?}, events = {:?}, dirfd = {}, path_addr = {:#x}",
+        fanotify_fd, flags, events, dirfd, path_addr
+    );
+
+    if flags.contains(MarkFlags::FAN_MARK_MOUNT | MarkFlags::FAN_MARK_FILESYSTEM) {
+        return_errno_with_message!(Errno::EINVAL, "the mark type is invalid");
+    }
+    let action =
+        flags & (MarkFlags::FAN_MARK_ADD | MarkFlags::FAN_MARK_REMOVE | MarkFlags::FAN_MARK_FLUSH);
+    if action.bits().count_ones() != 1 {
+        return_errno_with_message!(Errno::EINVAL, "exactly one action must be specified");
+    }
+
+    let fanotify_file = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        get_file_fast!(&mut file_table, fanotify_fd).into_owned()
+    };
+    let Some(fanotify_file) = fanotify_file.downcast_ref::<FanotifyFile>() else {
+        return_errno_with_message!(Errno::EINVAL, "the file is not a fanotify file");
+    };
+
+    if action == MarkFlags::FAN_MARK_FLUSH {
+        if flags.intersects(!(MarkFlags::FAN_MARK_FLUSH | MarkFlags::MARK_TYPES)) {
+            return_errno_with_message!(Errno::EINVAL, "invalid flags for flushing marks");
+        }
+        fanotify_file.flush_marks(|object| match object {
+            FsnotifyObject::Inode(_) => (flags & MarkFlags::MARK_TYPES).is_empty(),
+            FsnotifyObject::Mount(_) => flags.contains(MarkFlags::FAN_MARK_MOUNT),
+            FsnotifyObject::FileSystem(_) => flags.contains(MarkFlags::FAN_MARK_FILESYSTEM),
+        });
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    if events.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "the mask is empty");
+    }
+    if events.intersects(FsnotifyEvents::PERM_EVENTS)
+        && !fanotify_file.can_receive_permission_events()
+    {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "permission events require the content or pre-content class"
+        );
+    }
+
+    let path = if path_addr == 0 {
+        String::new()
+    } else {
+        let cstring = ctx.user_space().read_cstring(path_addr, MAX_FILENAME_LEN)?;
+        cstring.to_string_lossy().into_owned()
+    };
+    let dentry = {
+        let fs_path = FsPath::new(dirfd, path.as_ref())?;
+        let fs = ctx.posix_thread.fs().resolver().read();
+        if flags.contains(MarkFlags::FAN_MARK_DONT_FOLLOW) {
+            fs.lookup_no_follow(&fs_path)?
+        } else {
+            fs.lookup(&fs_path)?
+        }
+    };
+    if flags.contains(MarkFlags::FAN_MARK_ONLYDIR) && dentry.type_() != InodeType::Dir {
+        return_errno_with_message!(Errno::ENOTDIR, "the file is not a directory");
+    }
+
+    let object = if flags.contains(MarkFlags::FAN_MARK_MOUNT) {
+        FsnotifyObject::Mount(dentry.mount_node().clone())
+    } else if flags.contains(MarkFlags::FAN_MARK_FILESYSTEM) {
+        FsnotifyObject::FileSystem(dentry.fs())
+    } else {
+        FsnotifyObject::Inode(dentry.inode().clone())
+    };
+
+    let is_ignored = flags.contains(MarkFlags::FAN_MARK_IGNORED_MASK);
+    if action == MarkFlags::FAN_MARK_ADD {
+        fanotify_file.add_mark(
+            object,
+            events,
+            is_ignored,
+            flags.contains(MarkFlags::FAN_MARK_IGNORED_SURV_MODIFY),
+        )?;
+    } else {
+        fanotify_file.remove_mark(object, events, is_ignored)?;
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct MarkFlags: u32 {
+        const FAN_MARK_ADD = 0x1;
+        const FAN_MARK_REMOVE = 0x2;
+        const FAN_MARK_DONT_FOLLOW = 0x4;
+        const FAN_MARK_ONLYDIR = 0x8;
+        const FAN_MARK_MOUNT = 0x10;
+        const FAN_MARK_IGNORED_MASK = 0x20;
+        const FAN_MARK_IGNORED_SURV_MODIFY = 0x40;
+        const FAN_MARK_FLUSH = 0x80;
+        const FAN_MARK_FILESYSTEM = 0x100;
+
+        const MARK_TYPES = Self::FAN_MARK_MOUNT.bits() | Self::FAN_MARK_FILESYSTEM.bits();
+    }
+}
@@ -4,7 +4,7 @@ use super::SyscallReturn;
 use crate::{
     fs::{
         file_handle::FileLike,
-        file_table::{get_file_fast, FdFlags, FileDesc, WithFileTable},
+        file_table::{get_file_fast, FdFlags, FileDesc, FileTable, WithFileTable},
         utils::{
             FileRange, RangeLockItem, RangeLockItemBuilder, RangeLockType, StatusFlags, OFFSET_MAX,
         },
@@ -23,12 +23,22 @@ pub fn sys_fcntl(fd: FileDesc, cmd: i32, arg: u64, ctx: &Context) -> Result<Sysc
         FcntlCmd::F_SETFD => handle_setfd(fd, arg, ctx),
         FcntlCmd::F_GETFL => handle_getfl(fd, ctx),
         FcntlCmd::F_SETFL => handle_setfl(fd, arg, ctx),
-        FcntlCmd::F_GETLK => handle_getlk(fd, arg, ctx),
-        FcntlCmd::F_SETLK => handle_setlk(fd, arg, true, ctx),
-        FcntlCmd::F_SETLKW => handle_setlk(fd, arg, false, ctx).map_err(|err| match err.error() {
-            Errno::EINTR => Error::new(Errno::ERESTARTSYS),
-            _ => err,
-        }),
+        FcntlCmd::F_GETLK => handle_getlk(fd, arg, false, ctx),
+        FcntlCmd::F_SETLK => handle_setlk(fd, arg, true, false, ctx),
+        FcntlCmd::F_SETLKW => {
+            handle_setlk(fd, arg, false, false, ctx).map_err(|err| match err.error() {
+                Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+                _ => err,
+            })
+        }
+        FcntlCmd::F_OFD_GETLK => handle_getlk(fd, arg, true, ctx),
+        FcntlCmd::F_OFD_SETLK => handle_setlk(fd, arg, true, true, ctx),
+        FcntlCmd::F_OFD_SETLKW => {
+            handle_setlk(fd, arg, false, true, ctx).map_err(|err| match err.error() {
+                Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+                _ => err,
+            })
+        }
         FcntlCmd::F_GETOWN => handle_getown(fd, ctx),
         FcntlCmd::F_SETOWN => handle_setown(fd, arg, ctx),
     }
@@ -89,8 +99,9 @@ fn handle_setfl(fd: FileDesc, arg: u64, ctx: &Context) -> Result<SyscallReturn>
     Ok(SyscallReturn::Return(0))
 }
 
-fn handle_getlk(fd: FileDesc, arg: u64, ctx: &Context) -> Result<SyscallReturn> {
+fn handle_getlk(fd: FileDesc, arg: u64, is_ofd: bool, ctx: &Context) -> Result<SyscallReturn> {
     let mut file_table = ctx.thread_local.borrow_file_table_mut();
+    let table_owner = file_table.read_with(FileTable::range_lock_owner);
     let file = get_file_fast!(&mut file_table, fd);
     let lock_mut_ptr = arg as Vaddr;
     let mut lock_mut_c = ctx.user_space().read_val::<c_flock>(lock_mut_ptr)?;
@@ -98,11 +109,21 @@ fn handle_getlk(fd: FileDesc, arg: u64, ctx: &Context) -> Result<SyscallReturn>
     if lock_type == RangeLockType::Unlock {
         return_errno_with_message!(Errno::EINVAL, "invalid flock type for getlk");
     }
+    if is_ofd && lock_mut_c.l_pid != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the pid of an OFD lock must be zero");
+    }
+    let inode_file = file.as_inode_or_err()?;
+    let owner = if is_ofd {
+        inode_file.ofd_lock_owner()
+    } else {
+        table_owner
+    };
     let mut lock = RangeLockItemBuilder::new()
+        .owner(owner)
+        .pid(ctx.process.pid())
         .type_(lock_type)
         .range(from_c_flock_and_file(&lock_mut_c, &**file)?)
         .build()?;
-    let inode_file = file.as_inode_or_err()?;
     lock = inode_file.test_range_lock(lock)?;
     lock_mut_c.copy_from_range_lock(&lock);
     ctx.user_space().write_val(lock_mut_ptr, &lock_mut_c)?;
@@ -113,18 +134,30 @@ fn handle_setlk(
     fd: FileDesc,
     arg: u64,
     is_nonblocking: bool,
+    is_ofd: bool,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
     let mut file_table = ctx.thread_local.borrow_file_table_mut();
+    let table_owner = file_table.read_with(FileTable::range_lock_owner);
     let file = get_file_fast!(&mut file_table, fd);
     let lock_mut_ptr = arg as Vaddr;
     let lock_mut_c = ctx.user_space().read_val::<c_flock>(lock_mut_ptr)?;
     let lock_type = RangeLockType::try_from(lock_mut_c.l_type)?;
+    if is_ofd && lock_mut_c.l_pid != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the pid of an OFD lock must be zero");
+    }
+    let inode_file = file.as_inode_or_err()?;
+    let owner = if is_ofd {
+        inode_file.ofd_lock_owner()
+    } else {
+        table_owner
+    };
     let lock = RangeLockItemBuilder::new()
+        .owner(owner)
+        .pid(ctx.process.pid())
         .type_(lock_type)
         .range(from_c_flock_and_file(&lock_mut_c, &**file)?)
         .build()?;
-    let inode_file = file.as_inode_or_err()?;
     inode_file.set_range_lock(&lock, is_nonblocking)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -176,6 +209,9 @@ enum FcntlCmd {
     F_SETLKW = 7,
     F_SETOWN = 8,
     F_GETOWN = 9,
+    F_OFD_GETLK = 36,
+    F_OFD_SETLK = 37,
+    F_OFD_SETLKW = 38,
     F_DUPFD_CLOEXEC = 1030,
 }
 
@@ -203,8 +239,8 @@ pub struct c_flock {
     pub l_start: off_t,
     /// Size of the locked area, 0 means until EOF
     pub l_len: off_t,
-    /// Process holding the lock
-    pub l_pid: Pid,
+    /// Process holding the lock, which is -1 for OFD locks
+    pub l_pid: i32,
 }
 
 impl c_flock {
@@ -218,7 +254,7 @@ impl c_flock {
             } else {
                 lock.range().len() as off_t
             };
-            self.l_pid = lock.owner();
+            self.l_pid = if lock.is_ofd() { -1 } else { lock.pid() as i32 };
         }
     }
 }
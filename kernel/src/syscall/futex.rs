@@ -6,11 +6,13 @@ use crate::{
     current_userspace,
     prelude::*,
     process::posix_thread::futex::{
-        futex_op_and_flags_from_u32, futex_requeue, futex_wait, futex_wait_bitset, futex_wake,
-        futex_wake_bitset, FutexFlags, FutexOp,
+        futex2_size_and_flags_from_u32, futex_op_and_flags_from_u32, futex_requeue, futex_wait,
+        futex_wait_bitset, futex_waitv, futex_wake, futex_wake_bitset, FutexFlags, FutexOp,
+        FutexWaitv, FUTEX_WAITV_MAX,
     },
-    syscall::SyscallReturn,
+    syscall::{ClockId, SyscallReturn},
     time::{
+        clockid_t,
         clocks::{MonotonicClock, RealTimeClock},
         timer::Timeout,
         timespec_t,
@@ -145,3 +147,99 @@ pub fn sys_futex(
     debug!("futex returns, tid= {} ", ctx.posix_thread.tid());
     Ok(SyscallReturn::Return(res as _))
 }
+
+pub fn sys_futex_waitv(
+    waiters_addr: Vaddr,
+    nr_futexes: u32,
+    flags: u32,
+    timeout_addr: Vaddr,
+    clockid: clockid_t,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "waiters_addr = 0x{:x}, nr_futexes = {}, flags = 0x{:x}, timeout_addr = 0x{:x}, clockid = {}",
+        waiters_addr, nr_futexes, flags, timeout_addr, clockid
+    );
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the flags must be zero");
+    }
+    let nr_futexes = nr_futexes as usize;
+    if nr_futexes == 0 || nr_futexes > FUTEX_WAITV_MAX || waiters_addr == 0 {
+        return_errno_with_message!(Errno::EINVAL, "the number of futexes is invalid");
+    }
+
+    let timeout = if timeout_addr != 0 {
+        let timer_manager = match ClockId::try_from(clockid) {
+            Ok(ClockId::CLOCK_REALTIME) => RealTimeClock::timer_manager(),
+            Ok(ClockId::CLOCK_MONOTONIC) => MonotonicClock::timer_manager(),
+            _ => return_errno_with_message!(Errno::EINVAL, "the clock ID is not supported"),
+        };
+        let time_spec: timespec_t = ctx.user_space().read_val(timeout_addr)?;
+        // The timeout is an absolute time.
+        let timeout = Timeout::When(Duration::try_from(time_spec)?);
+        Some(ManagedTimeout::new_with_manager(timeout, timer_manager))
+    } else {
+        None
+    };
+
+    let futexes = read_futex_waitvs(waiters_addr, nr_futexes, ctx)?;
+
+    let index = futex_waitv(&futexes, timeout, ctx).map_err(|err| match err.error() {
+        Errno::ETIME => Error::new(Errno::ETIMEDOUT),
+        Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+        _ => err,
+    })?;
+
+    Ok(SyscallReturn::Return(index as _))
+}
+
+fn read_futex_waitvs(
+    waiters_addr: Vaddr,
+    nr_futexes: usize,
+    ctx: &Context,
+) -> Result<Vec<FutexWaitv>> {
+    let user_space = ctx.user_space();
+    let mut futexes = Vec::with_capacity(nr_futexes);
+
+    for i in 0..nr_futexes {
+        let waiter: c_futex_waitv =
+            user_space.read_val(waiters_addr + i * size_of::<c_futex_waitv>())?;
+        if waiter.reserved != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the reserved field must be zero");
+        }
+
+        let (size, futex_flags) = futex2_size_and_flags_from_u32(waiter.flags)?;
+        let addr = waiter.uaddr as Vaddr;
+        if addr % size.nbytes() != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the futex address is not aligned");
+        }
+        if waiter.val > size.max_val() {
+            return_errno_with_message!(Errno::EINVAL, "the futex value exceeds the futex size");
+        }
+
+        let pid = if futex_flags.contains(FutexFlags::FUTEX_PRIVATE) {
+            Some(ctx.process.pid())
+        } else {
+            None
+        };
+        futexes.push(FutexWaitv {
+            addr,
+            val: waiter.val,
+            size,
+            pid,
+        });
+    }
+
+    Ok(futexes)
+}
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/futex.h
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_futex_waitv {
+    val: u64,
+    uaddr: u64,
+    flags: u32,
+    reserved: u32,
+}
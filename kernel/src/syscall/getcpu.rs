@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::{cpu::PinCurrentCpu, task::disable_preempt};
+use ostd::{cpu::PinCurrentCpu, numa::node_of_cpu, task::disable_preempt};
 
 use super::SyscallReturn;
 use crate::prelude::*;
@@ -18,10 +18,12 @@ pub fn sys_getcpu(cpu: Vaddr, node: Vaddr, _tcache: Vaddr, ctx: &Context) -> Res
     // Since cpu and node can be NULL, we need to check them before writing
     if cpu != 0 {
         ctx.user_space()
-            .write_val::<usize>(cpu, &cpuid.as_usize())?;
+            .write_val::<u32>(cpu, &(cpuid.as_usize() as u32))?;
     }
     if node != 0 {
-        ctx.user_space().write_val::<usize>(node, &0)?; // TODO: NUMA is not supported
+        let node_id = node_of_cpu(cpuid);
+        ctx.user_space()
+            .write_val::<u32>(node, &(node_id.as_usize() as u32))?;
     }
     Ok(SyscallReturn::Return(0))
 }
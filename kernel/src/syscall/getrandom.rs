@@ -1,24 +1,34 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
-use crate::{device, prelude::*};
+use super::{splice::MAX_RW_COUNT, SyscallReturn};
+use crate::{prelude::*, util::random};
 
 pub fn sys_getrandom(buf: Vaddr, count: usize, flags: u32, ctx: &Context) -> Result<SyscallReturn> {
-    let flags = GetRandomFlags::from_bits_truncate(flags);
+    let flags = GetRandomFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
     debug!(
         "buf = 0x{:x}, count = 0x{:x}, flags = {:?}",
         buf, count, flags
     );
-    // TODO: support nonblock flag.
-    // Currently our getrandom implementation relies on x86-specific `rdrand` instruction, so it will never block.
-    let mut buffer = vec![0u8; count];
-    let read_len = if flags.contains(GetRandomFlags::GRND_RANDOM) {
-        device::Random::getrandom(&mut buffer)?
-    } else {
-        device::Urandom::getrandom(&mut buffer)?
-    };
-    ctx.user_space()
-        .write_bytes(buf, &mut VmReader::from(buffer.as_slice()))?;
+
+    if flags.contains(GetRandomFlags::GRND_INSECURE | GetRandomFlags::GRND_RANDOM) {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "GRND_INSECURE and GRND_RANDOM cannot be used together"
+        );
+    }
+
+    // `GRND_RANDOM` makes no difference since the blocking pool has been
+    // removed, as in Linux 5.6 and later.
+    if !flags.contains(GetRandomFlags::GRND_INSECURE) && !random::is_ready() {
+        if flags.contains(GetRandomFlags::GRND_NONBLOCK) {
+            return_errno_with_message!(Errno::EAGAIN, "the random number generator is not ready");
+        }
+        random::wait_until_ready()?;
+    }
+
+    let count = count.min(MAX_RW_COUNT);
+    let read_len = random::fill_writer(&mut ctx.user_space().writer(buf, count)?)?;
     Ok(SyscallReturn::Return(read_len as isize))
 }
 
@@ -2,8 +2,9 @@
 
 use super::{
     setxattr::{
-        check_xattr_namespace, lookup_dentry_for_xattr, parse_xattr_name,
-        read_xattr_name_cstr_from_user, XattrFileCtx,
+        check_xattr_permission, lookup_dentry_for_xattr, parse_xattr_name,
+        read_xattr_args_from_user, read_xattr_file_ctx_at, read_xattr_name_cstr_from_user,
+        XattrAccess, XattrFileCtx,
     },
     SyscallReturn,
 };
@@ -83,6 +84,34 @@ pub fn sys_fgetxattr(
     Ok(SyscallReturn::Return(len as _))
 }
 
+pub fn sys_getxattrat(
+    dirfd: FileDesc,
+    path_ptr: Vaddr,
+    at_flags: u32,
+    name_ptr: Vaddr,
+    args_ptr: Vaddr,
+    args_size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let file_ctx = read_xattr_file_ctx_at(dirfd, path_ptr, at_flags, &user_space)?;
+    let args = read_xattr_args_from_user(args_ptr, args_size, &user_space)?;
+    if args.flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "getxattrat does not accept flags");
+    }
+
+    let len = getxattr(
+        file_ctx,
+        name_ptr,
+        args.value as _,
+        args.size as _,
+        &user_space,
+        ctx,
+    )?;
+
+    Ok(SyscallReturn::Return(len as _))
+}
+
 fn getxattr(
     file_ctx: XattrFileCtx,
     name_ptr: Vaddr,
@@ -94,10 +123,10 @@ fn getxattr(
     let name_cstr = read_xattr_name_cstr_from_user(name_ptr, user_space)?;
     let name_str = name_cstr.to_string_lossy();
     let xattr_name = parse_xattr_name(name_str.as_ref())?;
-    check_xattr_namespace(xattr_name.namespace(), ctx).map_err(|_| Error::new(Errno::ENODATA))?;
 
     let mut value_writer = user_space.writer(value_ptr, value_len.min(XATTR_VALUE_MAX_LEN))?;
 
     let dentry = lookup_dentry_for_xattr(&file_ctx, ctx)?;
+    check_xattr_permission(&xattr_name, &dentry, XattrAccess::Read, ctx)?;
     dentry.get_xattr(xattr_name, &mut value_writer)
 }
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::{get_file_fast, FdFlags, FileDesc},
+        io_uring::{IoUringEnterFlags, IoUringFile, IoUringParams, IoUringRegisterOp},
+    },
+    prelude::*,
+};
+
+pub fn sys_io_uring_setup(
+    entries: u32,
+    params_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let mut params: IoUringParams = user_space.read_val(params_addr)?;
+    debug!("entries = {}, params = {:?}", entries, params);
+
+    if params.resv.iter().any(|resv| *resv != 0) {
+        return_errno_with_message!(Errno::EINVAL, "the reserved fields are not zero");
+    }
+    params.sq_entries = entries;
+
+    let io_uring = IoUringFile::new(&mut params)?;
+    user_space.write_val(params_addr, &params)?;
+
+    let file_table = ctx.thread_local.borrow_file_table();
+    let fd = file_table
+        .unwrap()
+        .write()
+        .insert(io_uring, FdFlags::CLOEXEC);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_io_uring_enter(
+    fd: FileDesc,
+    to_submit: u32,
+    min_complete: u32,
+    flags: u32,
+    _arg: Vaddr,
+    _arg_size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = IoUringEnterFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid enter flags"))?;
+    debug!(
+        "fd = {}, to_submit = {}, min_complete = {}, flags = {:?}",
+        fd, to_submit, min_complete, flags
+    );
+
+    if flags.intersects(
+        IoUringEnterFlags::IORING_ENTER_EXT_ARG | IoUringEnterFlags::IORING_ENTER_REGISTERED_RING,
+    ) {
+        return_errno_with_message!(Errno::EINVAL, "the enter flags are not supported");
+    }
+
+    let mut file_table = ctx.thread_local.borrow_file_table_mut();
+    let file = get_file_fast!(&mut file_table, fd).into_owned();
+    drop(file_table);
+    let io_uring = downcast_io_uring(file.as_ref())?;
+
+    let submitted = io_uring.submit(to_submit, ctx)?;
+
+    if flags.contains(IoUringEnterFlags::IORING_ENTER_GETEVENTS) {
+        // The number of submitted requests is returned even if the waiting is interrupted.
+        if let Err(err) = io_uring.wait_cqes(min_complete) {
+            if submitted == 0 {
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(SyscallReturn::Return(submitted as _))
+}
+
+pub fn sys_io_uring_register(
+    fd: FileDesc,
+    opcode: u32,
+    arg: Vaddr,
+    nr_args: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let op = IoUringRegisterOp::try_from(opcode)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid register opcode"))?;
+    debug!(
+        "fd = {}, op = {:?}, arg = 0x{:x}, nr_args = {}",
+        fd, op, arg, nr_args
+    );
+
+    let mut file_table = ctx.thread_local.borrow_file_table_mut();
+    let file = get_file_fast!(&mut file_table, fd).into_owned();
+    drop(file_table);
+    let io_uring = downcast_io_uring(file.as_ref())?;
+
+    let res = io_uring.register(op, arg, nr_args, ctx)?;
+    Ok(SyscallReturn::Return(res as _))
+}
+
+fn downcast_io_uring(file: &dyn FileLike) -> Result<&IoUringFile> {
+    file.downcast_ref::<IoUringFile>()
+        .ok_or_else(|| Error::with_message(Errno::EOPNOTSUPP, "the file is not an io_uring"))
+}
@@ -1,16 +1,19 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
+use super::{setxattr::is_capable, SyscallReturn};
 use crate::{
     fs::{
+        file_handle::FileLike,
         file_table::{get_file_fast, FdFlags, FileDesc, WithFileTable},
-        utils::{IoctlCmd, StatusFlags},
+        inode_handle::InodeHandle,
+        utils::{InodeFlags, IoctlCmd, StatusFlags},
     },
     prelude::*,
+    process::credentials::capabilities::CapSet,
 };
 
 pub fn sys_ioctl(fd: FileDesc, cmd: u32, arg: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
-    let ioctl_cmd = IoctlCmd::try_from(cmd)?;
+    let ioctl_cmd = IoctlCmd::decode(cmd)?;
     debug!(
         "fd = {}, ioctl_cmd = {:?}, arg = 0x{:x}",
         fd, ioctl_cmd, arg
@@ -57,6 +60,16 @@ pub fn sys_ioctl(fd: FileDesc, cmd: u32, arg: Vaddr, ctx: &Context) -> Result<Sy
                 Ok::<_, Error>(0)
             })?
         }
+        IoctlCmd::FS_IOC_GETFLAGS | IoctlCmd::FS_IOC32_GETFLAGS => {
+            let inode_flags = inode_handle_of(&**file)?.dentry().inode().flags();
+            ctx.user_space().write_val(arg, &inode_flags.bits())?;
+            0
+        }
+        IoctlCmd::FS_IOC_SETFLAGS | IoctlCmd::FS_IOC32_SETFLAGS => {
+            let bits = ctx.user_space().read_val::<u32>(arg)?;
+            set_inode_flags(inode_handle_of(&**file)?, bits, ctx)?;
+            0
+        }
         // FIXME: ioctl operations involving blocking I/O should be able to restart if interrupted
         _ => {
             let file_owned = file.into_owned();
@@ -69,3 +82,32 @@ pub fn sys_ioctl(fd: FileDesc, cmd: u32, arg: Vaddr, ctx: &Context) -> Result<Sy
     };
     Ok(SyscallReturn::Return(res as _))
 }
+
+fn inode_handle_of(file: &dyn FileLike) -> Result<&InodeHandle> {
+    file.downcast_ref().ok_or_else(|| {
+        Error::with_message(Errno::ENOTTY, "the file does not support the inode flags")
+    })
+}
+
+fn set_inode_flags(inode_handle: &InodeHandle, bits: u32, ctx: &Context) -> Result<()> {
+    let dentry = inode_handle.dentry();
+    let inode = dentry.inode();
+
+    if inode.owner()? != ctx.posix_thread.credentials().fsuid() && !is_capable(CapSet::FOWNER, ctx)
+    {
+        return_errno_with_message!(Errno::EPERM, "only the owner can set the inode flags");
+    }
+    let Some(new_flags) = InodeFlags::from_bits(bits) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the inode flags are not supported");
+    };
+    // Like Linux, only the privileged threads can change the immutable and append-only flags.
+    if new_flags != inode.flags() && !is_capable(CapSet::LINUX_IMMUTABLE, ctx) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "the immutable and append-only flags require CAP_LINUX_IMMUTABLE"
+        );
+    }
+
+    dentry.check_writable_mount()?;
+    inode.set_flags(new_flags)
+}
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::{
-    setxattr::{lookup_dentry_for_xattr, XattrFileCtx},
+    setxattr::{is_capable, lookup_dentry_for_xattr, read_xattr_file_ctx_at, XattrFileCtx},
     SyscallReturn,
 };
 use crate::{
@@ -75,6 +75,22 @@ pub fn sys_flistxattr(
     Ok(SyscallReturn::Return(len as _))
 }
 
+pub fn sys_listxattrat(
+    dirfd: FileDesc,
+    path_ptr: Vaddr,
+    at_flags: u32,
+    list_ptr: Vaddr, // The given list is used to place xattr (null-terminated) names.
+    list_len: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let file_ctx = read_xattr_file_ctx_at(dirfd, path_ptr, at_flags, &user_space)?;
+
+    let len = listxattr(file_ctx, list_ptr, list_len, &user_space, ctx)?;
+
+    Ok(SyscallReturn::Return(len as _))
+}
+
 fn listxattr(
     file_ctx: XattrFileCtx,
     list_ptr: Vaddr,
@@ -94,12 +110,7 @@ fn listxattr(
 }
 
 fn get_current_xattr_namespace(ctx: &Context) -> XattrNamespace {
-    let credentials = ctx.posix_thread.credentials();
-    let permitted_capset = credentials.permitted_capset();
-    let effective_capset = credentials.effective_capset();
-
-    if permitted_capset.contains(CapSet::SYS_ADMIN) && effective_capset.contains(CapSet::SYS_ADMIN)
-    {
+    if is_capable(CapSet::SYS_ADMIN, ctx) {
         XattrNamespace::Trusted
     } else {
         XattrNamespace::User
@@ -21,6 +21,18 @@ pub fn sys_lseek(fd: FileDesc, offset: isize, whence: u32, ctx: &Context) -> Res
         }
         1 => SeekFrom::Current(offset),
         2 => SeekFrom::End(offset),
+        3 => {
+            if offset < 0 {
+                return_errno!(Errno::ENXIO);
+            }
+            SeekFrom::Data(offset as usize)
+        }
+        4 => {
+            if offset < 0 {
+                return_errno!(Errno::ENXIO);
+            }
+            SeekFrom::Hole(offset as usize)
+        }
         _ => return_errno!(Errno::EINVAL),
     };
     let mut file_table = ctx.thread_local.borrow_file_table_mut();
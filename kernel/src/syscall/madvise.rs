@@ -45,6 +45,14 @@ pub fn sys_madvise(
             warn!("MADV_DONTNEED isn't implemented, do nothing for now.");
         }
         MadviseBehavior::MADV_FREE => madv_free(start, end, ctx)?,
+        MadviseBehavior::MADV_DONTDUMP => ctx
+            .user_space()
+            .root_vmar()
+            .set_dumpable(start..end, false)?,
+        MadviseBehavior::MADV_DODUMP => ctx
+            .user_space()
+            .root_vmar()
+            .set_dumpable(start..end, true)?,
         _ => todo!(),
     }
     Ok(SyscallReturn::Return(0))
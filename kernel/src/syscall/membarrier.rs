@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `membarrier` system call, which issues memory barriers on a set of threads.
+//!
+//! The barriers are issued by sending inter-processor interrupts to the CPUs that run the
+//! target threads. The CPUs are found by the [`VmSpace`]s activated on them, so the expedited
+//! commands are cheap if the process runs on a few CPUs.
+//!
+//! [`VmSpace`]: ostd::mm::VmSpace
+
+use core::{
+    hint::spin_loop,
+    sync::atomic::{fence, AtomicUsize, Ordering},
+};
+
+use ostd::{
+    cpu::{num_cpus, CpuSet},
+    smp::inter_processor_call,
+};
+
+use super::SyscallReturn;
+use crate::prelude::*;
+
+pub fn sys_membarrier(cmd: i32, flags: u32, cpu_id: i32, ctx: &Context) -> Result<SyscallReturn> {
+    debug!("cmd = {}, flags = {:#x}, cpu_id = {}", cmd, flags, cpu_id);
+
+    let Some(cmd) = u32::try_from(cmd).ok().and_then(MembarrierCmd::from_bits) else {
+        return_errno_with_message!(Errno::EINVAL, "the membarrier command is invalid");
+    };
+    // `MEMBARRIER_CMD_FLAG_CPU` is only valid for the RSEQ command, which is not supported.
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the membarrier flags are invalid");
+    }
+
+    let process_vm = ctx.process.vm();
+    let registrations = MembarrierCmd::from_bits_truncate(process_vm.membarrier_registrations());
+
+    let res = match cmd {
+        MembarrierCmd::QUERY => MembarrierCmd::SUPPORTED.bits() as isize,
+        MembarrierCmd::GLOBAL | MembarrierCmd::GLOBAL_EXPEDITED => {
+            // Any CPU may run a thread of some registered process, so all CPUs are interrupted.
+            // This is also enough for `MEMBARRIER_CMD_GLOBAL`, which must wait for all threads.
+            if num_cpus() > 1 {
+                run_on_cpus(CpuSet::new_full(), ipi_mb);
+            }
+            0
+        }
+        MembarrierCmd::PRIVATE_EXPEDITED => {
+            if !registrations.contains(MembarrierCmd::REGISTER_PRIVATE_EXPEDITED) {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the process is not registered for private expedited membarriers"
+                );
+            }
+            run_on_cpus(activated_cpus(ctx), ipi_mb);
+            0
+        }
+        MembarrierCmd::PRIVATE_EXPEDITED_SYNC_CORE => {
+            if !registrations.contains(MembarrierCmd::REGISTER_PRIVATE_EXPEDITED_SYNC_CORE) {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the process is not registered for core-serializing membarriers"
+                );
+            }
+            run_on_cpus(activated_cpus(ctx), ipi_sync_core);
+            0
+        }
+        MembarrierCmd::REGISTER_GLOBAL_EXPEDITED
+        | MembarrierCmd::REGISTER_PRIVATE_EXPEDITED
+        | MembarrierCmd::REGISTER_PRIVATE_EXPEDITED_SYNC_CORE => {
+            process_vm.register_membarrier(cmd.bits());
+            0
+        }
+        MembarrierCmd::GET_REGISTRATIONS => registrations.bits() as isize,
+        _ => return_errno_with_message!(Errno::EINVAL, "the membarrier command is not supported"),
+    };
+
+    Ok(SyscallReturn::Return(res))
+}
+
+bitflags! {
+    /// The commands of `membarrier`.
+    ///
+    /// Each command is a single bit, except `QUERY`, which is zero.
+    struct MembarrierCmd: u32 {
+        const QUERY                                 = 0;
+        const GLOBAL                                = 1 << 0;
+        const GLOBAL_EXPEDITED                      = 1 << 1;
+        const REGISTER_GLOBAL_EXPEDITED             = 1 << 2;
+        const PRIVATE_EXPEDITED                     = 1 << 3;
+        const REGISTER_PRIVATE_EXPEDITED            = 1 << 4;
+        const PRIVATE_EXPEDITED_SYNC_CORE           = 1 << 5;
+        const REGISTER_PRIVATE_EXPEDITED_SYNC_CORE  = 1 << 6;
+        const PRIVATE_EXPEDITED_RSEQ                = 1 << 7;
+        const REGISTER_PRIVATE_EXPEDITED_RSEQ       = 1 << 8;
+        const GET_REGISTRATIONS                     = 1 << 9;
+
+        /// The commands reported by `MEMBARRIER_CMD_QUERY`.
+        const SUPPORTED = Self::GLOBAL.bits
+            | Self::GLOBAL_EXPEDITED.bits
+            | Self::REGISTER_GLOBAL_EXPEDITED.bits
+            | Self::PRIVATE_EXPEDITED.bits
+            | Self::REGISTER_PRIVATE_EXPEDITED.bits
+            | Self::PRIVATE_EXPEDITED_SYNC_CORE.bits
+            | Self::REGISTER_PRIVATE_EXPEDITED_SYNC_CORE.bits
+            | Self::GET_REGISTRATIONS.bits;
+    }
+}
+
+/// Returns the CPUs that may run the threads of the current process.
+fn activated_cpus(ctx: &Context) -> CpuSet {
+    // Order the memory accesses of the caller before reading the CPU set. A CPU that activates
+    // the VM space later will observe the accesses.
+    fence(Ordering::SeqCst);
+    ctx.user_space().root_vmar().vm_space().activated_cpus()
+}
+
+/// Serializes the expedited commands, since they share [`NR_PENDING_IPIS`].
+static IPI_MUTEX: Mutex<()> = Mutex::new(());
+
+/// The number of the CPUs that have not executed the barrier.
+static NR_PENDING_IPIS: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs the barrier on the CPUs and waits for its completion.
+fn run_on_cpus(cpus: CpuSet, barrier: fn()) {
+    let _guard = IPI_MUTEX.lock();
+
+    NR_PENDING_IPIS.store(cpus.count(), Ordering::Relaxed);
+    inter_processor_call(&cpus, barrier);
+    while NR_PENDING_IPIS.load(Ordering::Acquire) != 0 {
+        spin_loop();
+    }
+}
+
+fn ipi_mb() {
+    fence(Ordering::SeqCst);
+    NR_PENDING_IPIS.fetch_sub(1, Ordering::Release);
+}
+
+fn ipi_sync_core() {
+    fence(Ordering::SeqCst);
+    ostd::cpu::sync_core();
+    NR_PENDING_IPIS.fetch_sub(1, Ordering::Release);
+}
@@ -10,11 +10,12 @@ use crate::{
     fs::{
         file_handle::FileLike,
         file_table::{get_file_fast, FileDesc},
+        inode_handle::InodeHandle,
     },
     prelude::*,
     vm::{
         perms::VmPerms,
-        vmar::is_userspace_vaddr,
+        vmar::{is_userspace_vaddr, vm_mapping::MappingName},
         vmo::{VmoOptions, VmoRightsOp},
     },
 };
@@ -124,36 +125,46 @@ fn do_sys_mmap(
                 options = options.vmo(shared_vmo);
             }
         } else {
-            let vmo = {
+            let (vmo, vmo_offset, dentry) = {
                 let mut file_table = ctx.thread_local.borrow_file_table_mut();
                 let file = get_file_fast!(&mut file_table, fd);
-                let inode_handle = file.as_inode_or_err()?;
 
-                let access_mode = inode_handle.access_mode();
-                if vm_perms.contains(VmPerms::READ) && !access_mode.is_readable() {
-                    return_errno!(Errno::EACCES);
+                if let Some(inode_handle) = file.downcast_ref::<InodeHandle>() {
+                    let access_mode = inode_handle.access_mode();
+                    if vm_perms.contains(VmPerms::READ) && !access_mode.is_readable() {
+                        return_errno!(Errno::EACCES);
+                    }
+                    if option.typ() == MMapType::Shared
+                        && vm_perms.contains(VmPerms::WRITE)
+                        && !access_mode.is_writable()
+                    {
+                        return_errno!(Errno::EACCES);
+                    }
+
+                    let inode = inode_handle.dentry().inode();
+                    let (vmo, vmo_offset) = if let Some(page_cache) = inode.page_cache() {
+                        (page_cache.to_dyn(), offset)
+                    } else if let Some(file_io) = inode_handle.file_io() {
+                        // The device files may provide their own VMOs.
+                        file_io.mmap_vmo(offset)?
+                    } else {
+                        return_errno_with_message!(Errno::EBADF, "File does not have page cache");
+                    };
+                    (vmo, vmo_offset, Some(inode_handle.dentry().clone()))
+                } else {
+                    // The files not backed by inodes may provide their own VMOs.
+                    let (vmo, vmo_offset) = file.mmap_vmo(offset)?;
+                    (vmo, vmo_offset, None)
                 }
-                if option.typ() == MMapType::Shared
-                    && vm_perms.contains(VmPerms::WRITE)
-                    && !access_mode.is_writable()
-                {
-                    return_errno!(Errno::EACCES);
-                }
-
-                let inode = inode_handle.dentry().inode();
-                inode
-                    .page_cache()
-                    .ok_or(Error::with_message(
-                        Errno::EBADF,
-                        "File does not have page cache",
-                    ))?
-                    .to_dyn()
             };
 
             options = options
                 .vmo(vmo)
-                .vmo_offset(offset)
+                .vmo_offset(vmo_offset)
                 .handle_page_faults_around();
+            if let Some(dentry) = dentry {
+                options = options.name(MappingName::File(dentry));
+            }
         }
 
         options
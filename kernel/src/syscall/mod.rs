@@ -3,16 +3,19 @@
 //! Read the Cpu ctx content then dispatch syscall to corresponding handler
 //! The each sub module contains functions that handle real syscall logic.
 pub use clock_gettime::ClockId;
-use ostd::cpu::context::UserContext;
+pub use eventfd::EventFile;
+use ostd::{cpu::context::UserContext, user::UserContextApi};
 pub use timer_create::create_timer;
 
 use crate::{context::Context, cpu::LinuxAbi, prelude::*};
 
 mod accept;
 mod access;
+mod adjtimex;
 mod alarm;
 mod arch;
 mod arch_prctl;
+pub mod audit;
 mod bind;
 mod brk;
 mod capget;
@@ -21,11 +24,14 @@ mod chdir;
 mod chmod;
 mod chown;
 mod chroot;
+mod clock_getres;
 mod clock_gettime;
+mod clock_settime;
 mod clone;
 mod close;
 mod connect;
 mod constants;
+mod copy_file_range;
 mod dup;
 mod epoll;
 mod eventfd;
@@ -33,6 +39,7 @@ mod execve;
 mod exit;
 mod exit_group;
 mod fallocate;
+mod fanotify;
 mod fcntl;
 mod flock;
 mod fork;
@@ -62,6 +69,7 @@ mod gettid;
 mod gettimeofday;
 mod getuid;
 mod getxattr;
+mod io_uring;
 mod ioctl;
 mod kill;
 mod link;
@@ -69,6 +77,7 @@ mod listen;
 mod listxattr;
 mod lseek;
 mod madvise;
+mod membarrier;
 mod mkdir;
 mod mknod;
 mod mmap;
@@ -89,9 +98,11 @@ mod prlimit64;
 mod pselect6;
 mod pwrite64;
 mod pwritev;
+mod quotactl;
 mod read;
 mod readlink;
 mod recvfrom;
+mod recvmmsg;
 mod recvmsg;
 mod removexattr;
 mod rename;
@@ -107,6 +118,7 @@ mod sched_get_priority_min;
 mod sched_getattr;
 mod sched_getparam;
 mod sched_getscheduler;
+mod sched_rr_get_interval;
 mod sched_setattr;
 mod sched_setparam;
 mod sched_setscheduler;
@@ -116,6 +128,7 @@ mod semctl;
 mod semget;
 mod semop;
 mod sendfile;
+mod sendmmsg;
 mod sendmsg;
 mod sendto;
 mod set_priority;
@@ -133,6 +146,7 @@ mod setresuid;
 mod setreuid;
 mod setsid;
 mod setsockopt;
+mod settimeofday;
 mod setuid;
 mod setxattr;
 mod shutdown;
@@ -140,6 +154,7 @@ mod sigaltstack;
 mod signalfd;
 mod socket;
 mod socketpair;
+mod splice;
 mod stat;
 mod statfs;
 mod statx;
@@ -341,13 +356,27 @@ impl SyscallArgument {
 
 pub fn handle_syscall(ctx: &Context, user_ctx: &mut UserContext) {
     let syscall_frame = SyscallArgument::new_from_context(user_ctx);
-    let syscall_return = arch::syscall_dispatch(
-        syscall_frame.syscall_number,
-        syscall_frame.args,
-        ctx,
-        user_ctx,
+    let syscall_info = ctx.posix_thread.syscall_info();
+    syscall_info.enter(
+        syscall_frame.syscall_number as usize,
+        &syscall_frame.args.map(|arg| arg as usize),
+        user_ctx.stack_pointer(),
+        user_ctx.instruction_pointer(),
     );
 
+    let syscall_return =
+        audit::audit_syscall(syscall_frame.syscall_number, &syscall_frame.args, ctx).and_then(
+            |()| {
+                arch::syscall_dispatch(
+                    syscall_frame.syscall_number,
+                    syscall_frame.args,
+                    ctx,
+                    user_ctx,
+                )
+            },
+        );
+    syscall_info.exit();
+
     match syscall_return {
         Ok(return_value) => {
             if let SyscallReturn::Return(return_value) = return_value {
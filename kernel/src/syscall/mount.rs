@@ -1,14 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_block::BlockDevice;
+
 use super::SyscallReturn;
 use crate::{
     fs::{
+        devpts::{DevPts, DevPtsOptions},
+        devtmpfs,
         exfat::{ExfatFS, ExfatMountOptions},
         ext2::Ext2,
+        file_table::get_file_fast,
         fs_resolver::{FsPath, AT_FDCWD},
+        fuse::{new_virtiofs, FuseDevFile, FuseFS, FuseMountOptions},
+        iso9660::{Iso9660FS, Iso9660MountOptions},
         overlayfs::OverlayFS,
-        path::Dentry,
+        path::{Dentry, PerMountFlags, PropagationType},
+        squashfs::SquashFS,
         utils::{FileSystem, InodeType},
+        v9fs::{V9fs, V9fsMountOptions},
+        vfat::{VfatFS, VfatMountOptions},
     },
     prelude::*,
     syscall::constants::MAX_FILENAME_LEN,
@@ -27,7 +37,12 @@ pub fn sys_mount(
     ctx: &Context,
 ) -> Result<SyscallReturn> {
     let user_space = ctx.user_space();
-    let devname = user_space.read_cstring(devname_addr, MAX_FILENAME_LEN)?;
+    // The source is ignored by some operations (e.g., remounting), so it can be NULL.
+    let devname = if devname_addr == 0 {
+        CString::default()
+    } else {
+        user_space.read_cstring(devname_addr, MAX_FILENAME_LEN)?
+    };
     let dirname = user_space.read_cstring(dirname_addr, MAX_FILENAME_LEN)?;
     let mount_flags = MountFlags::from_bits_truncate(flags as u32);
     debug!(
@@ -45,9 +60,9 @@ pub fn sys_mount(
     };
 
     if mount_flags.contains(MountFlags::MS_REMOUNT) && mount_flags.contains(MountFlags::MS_BIND) {
-        do_reconfigure_mnt()?;
+        do_reconfigure_mnt(dst_dentry, mount_flags)?;
     } else if mount_flags.contains(MountFlags::MS_REMOUNT) {
-        do_remount()?;
+        do_remount(dst_dentry, mount_flags)?;
     } else if mount_flags.contains(MountFlags::MS_BIND) {
         do_bind_mount(
             devname,
@@ -60,22 +75,44 @@ pub fn sys_mount(
         | mount_flags.contains(MountFlags::MS_SLAVE)
         | mount_flags.contains(MountFlags::MS_UNBINDABLE)
     {
-        do_change_type()?;
+        do_change_type(dst_dentry, mount_flags)?;
     } else if mount_flags.contains(MountFlags::MS_MOVE) {
         do_move_mount_old(devname, dst_dentry, ctx)?;
     } else {
-        do_new_mount(devname, fstype_addr, dst_dentry, data, ctx)?;
+        do_new_mount(devname, fstype_addr, dst_dentry, mount_flags, data, ctx)?;
     }
 
     Ok(SyscallReturn::Return(0))
 }
 
-fn do_reconfigure_mnt() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_reconfigure_mnt is not supported");
+/// Changes the per-mount flags of a mount without touching its file system,
+/// e.g., to make a bind mount read-only by `mount -o remount,bind,ro dst`.
+fn do_reconfigure_mnt(dst_dentry: Dentry, mount_flags: MountFlags) -> Result<()> {
+    check_mount_root(&dst_dentry)?;
+
+    let mount = dst_dentry.mount_node();
+    let mut per_mount_flags = to_per_mount_flags(mount_flags);
+    // Like Linux, the access time flags are kept if none of them is specified.
+    let atime_flags = PerMountFlags::NOATIME | PerMountFlags::NODIRATIME | PerMountFlags::RELATIME;
+    if !mount_flags.intersects(
+        MountFlags::MS_NOATIME
+            | MountFlags::MS_NODIRATIME
+            | MountFlags::MS_RELATIME
+            | MountFlags::MS_STRICTATIME,
+    ) {
+        per_mount_flags = (per_mount_flags - atime_flags) | (mount.flags() & atime_flags);
+    }
+    mount.set_flags(per_mount_flags);
+
+    Ok(())
 }
 
-fn do_remount() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_remount is not supported");
+/// Changes the flags of a mount and its file system.
+///
+/// Currently, no file system accepts new options when it is remounted,
+/// so only the per-mount flags are changed.
+fn do_remount(dst_dentry: Dentry, mount_flags: MountFlags) -> Result<()> {
+    do_reconfigure_mnt(dst_dentry, mount_flags)
 }
 
 /// Bind a mount to a dst location.
@@ -105,8 +142,37 @@ fn do_bind_mount(
     Ok(())
 }
 
-fn do_change_type() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_change_type is not supported");
+/// Changes the propagation type of a mount.
+///
+/// If `MS_REC` is specified, the type of all the mounts under it is changed as well.
+fn do_change_type(dst_dentry: Dentry, mount_flags: MountFlags) -> Result<()> {
+    check_mount_root(&dst_dentry)?;
+
+    let type_flags = mount_flags - MountFlags::MS_REC - MountFlags::MS_SILENT;
+    let type_ = if type_flags == MountFlags::MS_SHARED {
+        PropagationType::Shared
+    } else if type_flags == MountFlags::MS_PRIVATE {
+        PropagationType::Private
+    } else if type_flags == MountFlags::MS_SLAVE {
+        PropagationType::Slave
+    } else if type_flags == MountFlags::MS_UNBINDABLE {
+        PropagationType::Unbindable
+    } else {
+        return_errno_with_message!(Errno::EINVAL, "exactly one propagation type is expected");
+    };
+
+    dst_dentry
+        .mount_node()
+        .set_propagation(type_, mount_flags.contains(MountFlags::MS_REC));
+    Ok(())
+}
+
+/// Returns an error if the `Dentry` is not the root of its mount.
+fn check_mount_root(dentry: &Dentry) -> Result<()> {
+    if !dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "the path is not a mount point");
+    }
+    Ok(())
 }
 
 /// Move a mount from src location to dst location.
@@ -120,11 +186,17 @@ fn do_move_mount_old(src_name: CString, dst_dentry: Dentry, ctx: &Context) -> Re
         ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
     };
 
-    if !src_dentry.is_root_of_mount() {
+    check_mount_root(&src_dentry)?;
+    let Some(src_parent) = src_dentry.mount_node().parent() else {
         return_errno_with_message!(Errno::EINVAL, "src_name can not be moved");
     };
-    if src_dentry.mount_node().parent().is_none() {
-        return_errno_with_message!(Errno::EINVAL, "src_name can not be moved");
+    // Like Linux, a mount under a shared mount cannot be moved,
+    // since its copies in the peers would be left behind.
+    if src_parent
+        .upgrade()
+        .is_some_and(|parent| parent.is_shared())
+    {
+        return_errno_with_message!(Errno::EINVAL, "the parent mount of src_name is shared");
     }
 
     src_dentry.mount_node().graft_mount_node_tree(&dst_dentry)?;
@@ -137,6 +209,7 @@ fn do_new_mount(
     devname: CString,
     fs_type: Vaddr,
     target_dentry: Dentry,
+    mount_flags: MountFlags,
     data: Vaddr,
     ctx: &Context,
 ) -> Result<()> {
@@ -149,10 +222,28 @@ fn do_new_mount(
         return_errno_with_message!(Errno::EINVAL, "fs_type is empty");
     }
     let fs = get_fs(fs_type, devname, data, ctx)?;
-    target_dentry.mount(fs)?;
+    let mount = target_dentry.mount_with_flags(fs.clone(), to_per_mount_flags(mount_flags))?;
+    if let Some(devpts) = fs.downcast_ref::<DevPts>() {
+        devpts.set_mount_node(&mount);
+    }
+
     Ok(())
 }
 
+/// Converts the mount flags to the per-mount flags.
+///
+/// Like Linux, the access times are updated relatively by default.
+fn to_per_mount_flags(mount_flags: MountFlags) -> PerMountFlags {
+    let mut per_mount_flags = PerMountFlags::from(mount_flags);
+    if !mount_flags.contains(MountFlags::MS_NOATIME) {
+        per_mount_flags |= PerMountFlags::RELATIME;
+    }
+    if mount_flags.contains(MountFlags::MS_STRICTATIME) {
+        per_mount_flags -= PerMountFlags::RELATIME | PerMountFlags::NOATIME;
+    }
+    per_mount_flags
+}
+
 /// Get the filesystem by fs_type and devname.
 fn get_fs(
     fs_type: CString,
@@ -161,33 +252,96 @@ fn get_fs(
     ctx: &Context,
 ) -> Result<Arc<dyn FileSystem>> {
     let user_space = ctx.user_space();
-    let data = user_space.read_cstring(data, MAX_FILENAME_LEN)?;
+    let data = if data == 0 {
+        CString::default()
+    } else {
+        user_space.read_cstring(data, MAX_FILENAME_LEN)?
+    };
     let data = data.to_string_lossy();
 
     let fs_type = fs_type.to_str().unwrap();
     match fs_type {
-        "ext2" => {
-            let device = aster_block::get_device(devname.to_str().unwrap()).ok_or(
-                Error::with_message(Errno::ENOENT, "device for ext2 does not exist"),
-            )?;
+        "ext2" | "ext3" | "ext4" => {
+            let device = get_block_device(&devname, ctx)?;
             let ext2_fs = Ext2::open(device)?;
             Ok(ext2_fs)
         }
         "exfat" => {
-            let device = aster_block::get_device(devname.to_str().unwrap()).ok_or(
-                Error::with_message(Errno::ENOENT, "device for exfat does not exist"),
-            )?;
+            let device = get_block_device(&devname, ctx)?;
             let exfat_fs = ExfatFS::open(device, ExfatMountOptions::default())?;
             Ok(exfat_fs)
         }
+        "vfat" | "msdos" => {
+            let device = get_block_device(&devname, ctx)?;
+            let options = VfatMountOptions::parse(data.as_ref())?;
+            let vfat_fs = VfatFS::open(device, options)?;
+            Ok(vfat_fs)
+        }
+        "iso9660" => {
+            let device = get_block_device(&devname, ctx)?;
+            let options = Iso9660MountOptions::parse(data.as_ref())?;
+            let iso9660_fs = Iso9660FS::open(device, options)?;
+            Ok(iso9660_fs)
+        }
+        "squashfs" => {
+            let device = get_block_device(&devname, ctx)?;
+            let squash_fs = SquashFS::open(device)?;
+            Ok(squash_fs)
+        }
+        "devpts" => {
+            let options = DevPtsOptions::parse(data.as_ref())?;
+            Ok(DevPts::with_options(options))
+        }
+        "devtmpfs" => Ok(devtmpfs::singleton().clone()),
         "overlay" => {
             let overlay_fs = create_overlayfs(data.as_ref(), ctx)?;
             Ok(overlay_fs)
         }
+        // The subtypes, e.g., `fuse.sshfs`, are mounted by `fusermount`.
+        _ if fs_type == "fuse" || fs_type.starts_with("fuse.") => {
+            let fuse_fs = create_fuse(data.as_ref(), ctx)?;
+            Ok(fuse_fs)
+        }
+        // The tag of the virtio-fs device is given as the device name.
+        "virtiofs" => {
+            let options = FuseMountOptions::parse_virtiofs(data.as_ref())?;
+            let virtiofs = new_virtiofs(devname.to_str().unwrap(), options)?;
+            Ok(virtiofs)
+        }
+        // The tag of the virtio-9p device is given as the device name.
+        "9p" => {
+            let options = V9fsMountOptions::parse(data.as_ref())?;
+            let v9fs = V9fs::new(devname.to_str().unwrap(), options)?;
+            Ok(v9fs)
+        }
         _ => return_errno_with_message!(Errno::EINVAL, "Invalid fs type"),
     }
 }
 
+/// Returns the block device to mount.
+///
+/// The device name is either the name of a block device (e.g., `vext2`) or the path to a block
+/// device file (e.g., `/dev/vext2p1`, `/dev/loop0`, or `/dev/dm-0`).
+fn get_block_device(devname: &CStr, ctx: &Context) -> Result<Arc<dyn BlockDevice>> {
+    let devname = devname.to_str().unwrap();
+    if let Some(device) = aster_block::get_device(devname) {
+        return Ok(device);
+    }
+
+    let dentry = {
+        let fs_path = FsPath::try_from(devname)?;
+        ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
+    };
+    let inode = dentry.inode();
+    if inode.type_() != InodeType::BlockDevice {
+        return_errno_with_message!(Errno::ENOTBLK, "the file is not a block device");
+    }
+    let Some(device) = inode.as_device() else {
+        return_errno_with_message!(Errno::ENXIO, "the block device does not exist");
+    };
+    crate::device::open_block_device(device.id())
+}
+
 // TODO: Support read-only mount (no upper) and customized features
 fn create_overlayfs(data: &str, ctx: &Context) -> Result<Arc<OverlayFS>> {
     let mut lower = Vec::new();
@@ -222,19 +376,45 @@ fn create_overlayfs(data: &str, ctx: &Context) -> Result<Arc<OverlayFS>> {
         }
     }
 
+    if lower.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "lowerdir is missing");
+    }
+    if upper.is_empty() || work.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "upperdir or workdir is missing");
+    }
+
     let fs = ctx.posix_thread.fs().resolver().read();
 
     let upper = fs.lookup(&FsPath::new(AT_FDCWD, upper)?)?;
     let lower = lower
         .iter()
-        .map(|lower| fs.lookup(&FsPath::new(AT_FDCWD, lower).unwrap()).unwrap())
-        .collect();
+        .map(|lower| fs.lookup(&FsPath::new(AT_FDCWD, lower)?))
+        .collect::<Result<Vec<_>>>()?;
     let work = fs.lookup(&FsPath::new(AT_FDCWD, work)?)?;
 
     let overlayfs = OverlayFS::new(upper, lower, work)?;
     Ok(overlayfs)
 }
 
+fn create_fuse(data: &str, ctx: &Context) -> Result<Arc<FuseFS>> {
+    let options = FuseMountOptions::parse(data)?;
+
+    let mut file_table = ctx.thread_local.borrow_file_table_mut();
+    let fd = options
+        .fd()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the FUSE mount option fd is missing"))?;
+    let file = get_file_fast!(&mut file_table, fd);
+    let conn = file
+        .as_inode_or_err()?
+        .file_io()
+        .and_then(|file_io| file_io.downcast_ref::<FuseDevFile>())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the file is not /dev/fuse"))?
+        .conn()
+        .clone();
+
+    FuseFS::new(conn, options)
+}
+
 bitflags! {
     struct MountFlags: u32 {
         const MS_RDONLY        =   1 << 0;       // Mount read-only.
@@ -259,5 +439,18 @@ bitflags! {
         const MS_SHARED        =   1 << 20;      // Change to shared.
         const MS_RELATIME      =   1 << 21; 	 // Update atime relative to mtime/ctime.
         const MS_KERNMOUNT     =   1 << 22;      // This is a kern_mount call.
+        const MS_STRICTATIME   =   1 << 24;      // Always update access times.
+    }
+}
+
+impl From<MountFlags> for PerMountFlags {
+    fn from(flags: MountFlags) -> Self {
+        // The values are the same except for `MS_RELATIME`.
+        let mut per_mount_flags =
+            PerMountFlags::from_bits_truncate(flags.bits()) - PerMountFlags::RELATIME;
+        if flags.contains(MountFlags::MS_RELATIME) {
+            per_mount_flags |= PerMountFlags::RELATIME;
+        }
+        per_mount_flags
     }
 }
@@ -4,8 +4,8 @@ use super::SyscallReturn;
 use crate::{
     fs::{
         file_table::{FdFlags, FileDesc},
-        fs_resolver::{FsPath, AT_FDCWD},
-        utils::{AccessMode, CreationFlags},
+        fs_resolver::{FsPath, ResolveFlags, AT_FDCWD},
+        utils::{AccessMode, CreationFlags, StatusFlags},
     },
     prelude::*,
     syscall::constants::MAX_FILENAME_LEN,
@@ -24,6 +24,35 @@ pub fn sys_openat(
         dirfd, path, flags, mode
     );
 
+    do_openat(dirfd, path, flags, mode, ResolveFlags::empty(), ctx)
+}
+
+pub fn sys_openat2(
+    dirfd: FileDesc,
+    path_addr: Vaddr,
+    how_addr: Vaddr,
+    size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let path = ctx.user_space().read_cstring(path_addr, MAX_FILENAME_LEN)?;
+    let open_how = read_open_how_from_user(how_addr, size, ctx)?;
+    debug!(
+        "dirfd = {}, path = {:?}, open_how = {:?}",
+        dirfd, path, open_how
+    );
+
+    let (flags, mode, resolve_flags) = open_how.check()?;
+    do_openat(dirfd, path, flags, mode, resolve_flags, ctx)
+}
+
+fn do_openat(
+    dirfd: FileDesc,
+    path: CString,
+    flags: u32,
+    mode: u16,
+    resolve_flags: ResolveFlags,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
     let current = ctx.posix_thread;
     let file_handle = {
         let path = path.to_string_lossy();
@@ -33,7 +62,7 @@ pub fn sys_openat(
             .fs()
             .resolver()
             .read()
-            .open(&fs_path, flags, mask_mode)
+            .open_with_resolve_flags(&fs_path, flags, mask_mode, resolve_flags)
             .map_err(|err| match err.error() {
                 Errno::EINTR => Error::new(Errno::ERESTARTSYS),
                 _ => err,
@@ -65,3 +94,103 @@ pub fn sys_creat(path_addr: Vaddr, mode: u16, ctx: &Context) -> Result<SyscallRe
         AccessMode::O_WRONLY as u32 | CreationFlags::O_CREAT.bits() | CreationFlags::O_TRUNC.bits();
     self::sys_openat(AT_FDCWD, path_addr, flags, mode, ctx)
 }
+
+/// The arguments of `openat2`.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.13/source/include/uapi/linux/openat2.h#L19>.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+struct OpenHow {
+    flags: u64,
+    mode: u64,
+    resolve: u64,
+}
+
+/// The flag that is accepted by `openat2` but has no effect on 64-bit systems.
+const O_LARGEFILE: u32 = 1 << 15;
+
+/// The mask of the access mode in the open flags.
+const O_ACCMODE: u32 = 0b11;
+
+impl OpenHow {
+    /// Checks the arguments and returns the open flags, the mode, and the resolve flags.
+    ///
+    /// Unlike `openat`, `openat2` rejects the unknown flags and the meaningless mode.
+    fn check(&self) -> Result<(u32, u16, ResolveFlags)> {
+        let valid_flags =
+            CreationFlags::all().bits() | StatusFlags::all().bits() | O_ACCMODE | O_LARGEFILE;
+        let flags = u32::try_from(self.flags)
+            .ok()
+            .filter(|flags| flags & !valid_flags == 0)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid open flags"))?;
+        let creation_flags = CreationFlags::from_bits_truncate(flags);
+
+        let is_creating = creation_flags.contains(CreationFlags::O_CREAT)
+            || creation_flags.contains(CreationFlags::_O_TMPFILE);
+        if is_creating {
+            if self.mode & !0o7777 != 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid mode");
+            }
+        } else if self.mode != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the mode is only allowed for creation");
+        }
+
+        // Like Linux, only a few flags make sense for `O_PATH`.
+        if StatusFlags::from_bits_truncate(flags).contains(StatusFlags::O_PATH) {
+            let o_path_flags = StatusFlags::O_PATH.bits()
+                | (CreationFlags::O_DIRECTORY
+                    | CreationFlags::O_NOFOLLOW
+                    | CreationFlags::O_CLOEXEC)
+                    .bits();
+            if flags & !o_path_flags != 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid flags for O_PATH");
+            }
+        }
+
+        let resolve_flags = ResolveFlags::from_bits(self.resolve)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid resolve flags"))?;
+        if resolve_flags.contains(ResolveFlags::RESOLVE_BENEATH | ResolveFlags::RESOLVE_IN_ROOT) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "RESOLVE_BENEATH and RESOLVE_IN_ROOT are mutually exclusive"
+            );
+        }
+        // The lookups with `RESOLVE_CACHED` cannot modify the file system.
+        if resolve_flags.contains(ResolveFlags::RESOLVE_CACHED)
+            && (is_creating || creation_flags.contains(CreationFlags::O_TRUNC))
+        {
+            return_errno_with_message!(
+                Errno::EAGAIN,
+                "RESOLVE_CACHED cannot be used to create or truncate files"
+            );
+        }
+
+        Ok((flags, self.mode as u16, resolve_flags))
+    }
+}
+
+/// Reads the `OpenHow` that may be extended in the future.
+///
+/// Like Linux, the bytes beyond the known fields must be zeros.
+fn read_open_how_from_user(addr: Vaddr, size: usize, ctx: &Context) -> Result<OpenHow> {
+    let type_size = size_of::<OpenHow>();
+    if size < type_size {
+        return_errno_with_message!(Errno::EINVAL, "the size of open_how is too small");
+    }
+    if size > PAGE_SIZE {
+        return_errno_with_message!(Errno::E2BIG, "the size of open_how is too large");
+    }
+
+    let space = ctx.user_space();
+    let open_how: OpenHow = space.read_val(addr)?;
+
+    if size > type_size {
+        let mut buf = vec![0u8; size - type_size];
+        space.read_bytes(addr + type_size, &mut VmWriter::from(buf.as_mut_slice()))?;
+        if buf.iter().any(|&byte| byte != 0) {
+            return_errno_with_message!(Errno::E2BIG, "unknown fields of open_how are set");
+        }
+    }
+
+    Ok(open_how)
+}
@@ -11,6 +11,7 @@ use crate::{
     },
     prelude::*,
     process::{signal::Poller, ResourceType},
+    time::{clocks::MonotonicClock, Clock},
 };
 
 pub fn sys_poll(fds: Vaddr, nfds: u32, timeout: i32, ctx: &Context) -> Result<SyscallReturn> {
@@ -20,7 +21,17 @@ pub fn sys_poll(fds: Vaddr, nfds: u32, timeout: i32, ctx: &Context) -> Result<Sy
         None
     };
 
-    do_sys_poll(fds, nfds, timeout, ctx)
+    let res = do_sys_poll(fds, nfds, timeout, ctx);
+
+    // Unlike `ppoll`, the timeout cannot be updated in the user space. Linux restarts `poll`
+    // with the remaining time via `restart_syscall`, which we do not support yet. So `poll` with
+    // a timeout is never restarted.
+    match res {
+        Err(err) if err.error() == Errno::ERESTARTNOHAND && timeout.is_some() => {
+            Err(Error::new(Errno::EINTR))
+        }
+        res => res,
+    }
 }
 
 pub fn do_sys_poll(
@@ -102,6 +113,10 @@ pub fn do_poll(poll_fds: &[PollFd], timeout: Option<&Duration>, ctx: &Context) -
             // We should return zero if the timeout expires
             // before any file descriptors are ready.
             Err(err) if err.error() == Errno::ETIME => return Ok(0),
+            // Like Linux, the syscall is restarted if no signal handler is invoked.
+            Err(err) if err.error() == Errno::EINTR => {
+                return Err(Error::new(Errno::ERESTARTNOHAND))
+            }
             Err(err) => return Err(err),
         };
 
@@ -112,6 +127,47 @@ pub fn do_poll(poll_fds: &[PollFd], timeout: Option<&Duration>, ctx: &Context) -
     }
 }
 
+/// A timeout whose remaining time can be queried.
+///
+/// `select`, `pselect6`, and `ppoll` write the remaining time back to the user space, so that
+/// the syscalls only wait for the remaining time if they are restarted.
+pub(super) struct RemainingTimeout {
+    deadline: Duration,
+}
+
+impl RemainingTimeout {
+    /// Starts the timeout.
+    pub(super) fn new(timeout: Duration) -> Self {
+        let deadline = MonotonicClock::get().read_time().saturating_add(timeout);
+        Self { deadline }
+    }
+
+    /// Returns the remaining time, which is zero if the timeout has expired.
+    pub(super) fn remaining(&self) -> Duration {
+        self.deadline
+            .saturating_sub(MonotonicClock::get().read_time())
+    }
+}
+
+/// Writes the remaining time back to the user space after an operation with a timeout.
+///
+/// If the remaining time cannot be written, the interrupted syscall cannot be restarted with the
+/// remaining time, so it fails with `EINTR` instead.
+pub(super) fn write_back_remaining<R>(
+    res: Result<R>,
+    timeout: Option<&RemainingTimeout>,
+    write_back: impl FnOnce(Duration) -> Result<()>,
+) -> Result<R> {
+    let Some(timeout) = timeout else {
+        return res;
+    };
+
+    match (res, write_back(timeout.remaining())) {
+        (Err(err), Err(_)) if err.error() == Errno::ERESTARTNOHAND => Err(Error::new(Errno::EINTR)),
+        (res, _) => res,
+    }
+}
+
 struct PollFiles<'a> {
     poll_fds: &'a [PollFd],
     files: CowFiles<'a>,
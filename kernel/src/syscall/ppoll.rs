@@ -2,10 +2,13 @@
 
 use core::time::Duration;
 
-use super::{poll::do_sys_poll, SyscallReturn};
+use super::{
+    poll::{do_sys_poll, write_back_remaining, RemainingTimeout},
+    SyscallReturn,
+};
 use crate::{
     prelude::*,
-    process::signal::{sig_mask::SigMask, with_sigmask_changed},
+    process::signal::{sig_mask::SigMask, with_sigmask_replaced},
     time::timespec_t,
 };
 
@@ -25,24 +28,25 @@ pub fn sys_ppoll(
     } else {
         None
     };
+    // Like Linux, a zero timeout is not written back.
+    let remaining = timeout
+        .filter(|timeout| !timeout.is_zero())
+        .map(RemainingTimeout::new);
 
-    if sigmask_addr != 0 {
+    let res = if sigmask_addr != 0 {
         if sigmask_size != size_of::<SigMask>() {
             return_errno_with_message!(Errno::EINVAL, "invalid sigmask size");
         }
 
         let sigmask = user_space.read_val::<SigMask>(sigmask_addr)?;
-        with_sigmask_changed(ctx, |_| sigmask, || do_sys_poll(fds, nfds, timeout, ctx))
+        with_sigmask_replaced(ctx, sigmask, || do_sys_poll(fds, nfds, timeout, ctx))
     } else {
         do_sys_poll(fds, nfds, timeout, ctx)
-    }
+    };
 
-    // TODO: Write back the remaining time to `timespec_addr`.
-    //
-    // The ppoll system call should write back the remaining time,
-    // yet the function counterpart in libc hides this behavior to
-    // make the API more portable across different UNIX-like OSes.
-    // For the maximized Linux compatibility, we should follow Linux's behavior.
-    // But this cannot be readily achieved given how our internal synchronization primitives
-    // such as `Pause` and `WaitTimeout` work.
+    // The remaining time is written back, so a restarted `ppoll` waits for the remaining time
+    // only. The glibc wrapper hides this behavior, but the raw syscall exposes it.
+    write_back_remaining(res, remaining.as_ref(), |remaining| {
+        user_space.write_val(timespec_addr, &timespec_t::from(remaining))
+    })
 }
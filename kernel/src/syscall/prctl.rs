@@ -3,7 +3,14 @@
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    process::{posix_thread::MAX_THREAD_NAME_LEN, signal::sig_num::SigNum},
+    process::{
+        posix_thread::{thread_table, MAX_THREAD_NAME_LEN},
+        process_table,
+        signal::sig_num::SigNum,
+        Pid, Process,
+    },
+    sched::new_core_cookie,
+    thread::{AsThread, Thread},
 };
 
 pub fn sys_prctl(
@@ -31,15 +38,19 @@ pub fn sys_prctl(
             ctx.user_space().write_val(write_to_addr, &write_val)?;
         }
         PrctlCmd::PR_GET_DUMPABLE => {
-            // TODO: when coredump is supported, return the actual value
-            return Ok(SyscallReturn::Return(Dumpable::Disable as _));
+            let dumpable = if ctx.process.is_dumpable() {
+                Dumpable::User
+            } else {
+                Dumpable::Disable
+            };
+            return Ok(SyscallReturn::Return(dumpable as _));
         }
         PrctlCmd::PR_SET_DUMPABLE(dumpable) => {
             if dumpable != Dumpable::Disable && dumpable != Dumpable::User {
                 return_errno!(Errno::EINVAL)
             }
 
-            // TODO: implement coredump
+            ctx.process.set_dumpable(dumpable == Dumpable::User);
         }
         PrctlCmd::PR_GET_KEEPCAPS => {
             let keep_cap = {
@@ -93,11 +104,107 @@ pub fn sys_prctl(
             ctx.user_space()
                 .write_val(write_addr, &(process.is_child_subreaper() as u32))?;
         }
+        PrctlCmd::PR_SCHED_CORE(sched_core) => handle_sched_core(sched_core, ctx)?,
         _ => todo!(),
     }
     Ok(SyscallReturn::Return(0))
 }
 
+fn handle_sched_core(sched_core: SchedCoreCmd, ctx: &Context) -> Result<()> {
+    match sched_core {
+        SchedCoreCmd::Get {
+            pid,
+            pid_type,
+            write_to_addr,
+        } => {
+            if pid_type != SchedCorePidType::Pid {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the cookie can only be got from a single thread"
+                );
+            }
+            let thread = sched_core_targets(pid, pid_type, ctx)?.remove(0);
+            ctx.user_space()
+                .write_val(write_to_addr, &thread.sched_attr().core_cookie())?;
+        }
+        SchedCoreCmd::Create { pid, pid_type } => {
+            let cookie = new_core_cookie();
+            for thread in sched_core_targets(pid, pid_type, ctx)? {
+                thread.sched_attr().set_core_cookie(cookie);
+            }
+        }
+        SchedCoreCmd::ShareTo { pid, pid_type } => {
+            let cookie = ctx.thread.sched_attr().core_cookie();
+            for thread in sched_core_targets(pid, pid_type, ctx)? {
+                thread.sched_attr().set_core_cookie(cookie);
+            }
+        }
+        SchedCoreCmd::ShareFrom { pid, pid_type } => {
+            if pid_type != SchedCorePidType::Pid {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the cookie can only be shared from a single thread"
+                );
+            }
+            let thread = sched_core_targets(pid, pid_type, ctx)?.remove(0);
+            let cookie = thread.sched_attr().core_cookie();
+            ctx.thread.sched_attr().set_core_cookie(cookie);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the threads specified by `pid` and `pid_type`.
+///
+/// A `pid` of zero refers to the current thread, process, or process group.
+fn sched_core_targets(
+    pid: Pid,
+    pid_type: SchedCorePidType,
+    ctx: &Context,
+) -> Result<Vec<Arc<Thread>>> {
+    let threads_of = |process: &Process| -> Vec<Arc<Thread>> {
+        process
+            .tasks()
+            .lock()
+            .as_slice()
+            .iter()
+            .filter_map(|task| task.as_thread().cloned())
+            .collect()
+    };
+
+    let threads = match pid_type {
+        SchedCorePidType::Pid if pid == 0 => vec![current_thread!()],
+        SchedCorePidType::Pid => {
+            let thread = thread_table::get_thread(pid)
+                .ok_or_else(|| Error::with_message(Errno::ESRCH, "the thread does not exist"))?;
+            vec![thread]
+        }
+        SchedCorePidType::Tgid if pid == 0 => threads_of(ctx.process),
+        SchedCorePidType::Tgid => {
+            let process = process_table::get_process(pid)
+                .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?;
+            threads_of(&process)
+        }
+        SchedCorePidType::Pgid => {
+            let pgid = if pid == 0 { ctx.process.pgid() } else { pid };
+            let process_group = process_table::get_process_group(&pgid).ok_or_else(|| {
+                Error::with_message(Errno::ESRCH, "the process group does not exist")
+            })?;
+            let processes: Vec<Arc<Process>> = process_group.lock().iter().cloned().collect();
+            processes
+                .iter()
+                .flat_map(|process| threads_of(process))
+                .collect()
+        }
+    };
+
+    if threads.is_empty() {
+        return_errno_with_message!(Errno::ESRCH, "no thread is found");
+    }
+    Ok(threads)
+}
+
 const PR_SET_PDEATHSIG: i32 = 1;
 const PR_GET_PDEATHSIG: i32 = 2;
 const PR_GET_DUMPABLE: i32 = 3;
@@ -110,6 +217,12 @@ const PR_SET_TIMERSLACK: i32 = 29;
 const PR_GET_TIMERSLACK: i32 = 30;
 const PR_SET_CHILD_SUBREAPER: i32 = 36;
 const PR_GET_CHILD_SUBREAPER: i32 = 37;
+const PR_SCHED_CORE: i32 = 62;
+
+const PR_SCHED_CORE_GET: u64 = 0;
+const PR_SCHED_CORE_CREATE: u64 = 1;
+const PR_SCHED_CORE_SHARE_TO: u64 = 2;
+const PR_SCHED_CORE_SHARE_FROM: u64 = 3;
 
 #[expect(non_camel_case_types)]
 #[derive(Debug, Clone, Copy)]
@@ -128,6 +241,37 @@ pub enum PrctlCmd {
     PR_GET_DUMPABLE,
     PR_SET_CHILD_SUBREAPER(bool),
     PR_GET_CHILD_SUBREAPER(Vaddr),
+    PR_SCHED_CORE(SchedCoreCmd),
+}
+
+/// The subcommands of `PR_SCHED_CORE`, which manage the cookies of core scheduling.
+#[derive(Debug, Clone, Copy)]
+pub enum SchedCoreCmd {
+    Get {
+        pid: Pid,
+        pid_type: SchedCorePidType,
+        write_to_addr: Vaddr,
+    },
+    Create {
+        pid: Pid,
+        pid_type: SchedCorePidType,
+    },
+    ShareTo {
+        pid: Pid,
+        pid_type: SchedCorePidType,
+    },
+    ShareFrom {
+        pid: Pid,
+        pid_type: SchedCorePidType,
+    },
+}
+
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum SchedCorePidType {
+    Pid = 0,
+    Tgid = 1,
+    Pgid = 2,
 }
 
 #[repr(u64)]
@@ -139,7 +283,7 @@ pub enum Dumpable {
 }
 
 impl PrctlCmd {
-    fn from_args(option: i32, arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> Result<PrctlCmd> {
+    fn from_args(option: i32, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> Result<PrctlCmd> {
         match option {
             PR_SET_PDEATHSIG => {
                 let signum = SigNum::try_from(arg2 as u8)?;
@@ -156,6 +300,25 @@ impl PrctlCmd {
             PR_SET_KEEPCAPS => Ok(PrctlCmd::PR_SET_KEEPCAPS(arg2 as _)),
             PR_SET_CHILD_SUBREAPER => Ok(PrctlCmd::PR_SET_CHILD_SUBREAPER(arg2 > 0)),
             PR_GET_CHILD_SUBREAPER => Ok(PrctlCmd::PR_GET_CHILD_SUBREAPER(arg2 as _)),
+            PR_SCHED_CORE => {
+                let pid = arg3 as Pid;
+                let pid_type = SchedCorePidType::try_from(arg4)?;
+                let cmd = match arg2 {
+                    PR_SCHED_CORE_GET => SchedCoreCmd::Get {
+                        pid,
+                        pid_type,
+                        write_to_addr: arg5 as _,
+                    },
+                    PR_SCHED_CORE_CREATE => SchedCoreCmd::Create { pid, pid_type },
+                    PR_SCHED_CORE_SHARE_TO => SchedCoreCmd::ShareTo { pid, pid_type },
+                    PR_SCHED_CORE_SHARE_FROM => SchedCoreCmd::ShareFrom { pid, pid_type },
+                    _ => return_errno_with_message!(
+                        Errno::EINVAL,
+                        "unsupported PR_SCHED_CORE subcommand"
+                    ),
+                };
+                Ok(PrctlCmd::PR_SCHED_CORE(cmd))
+            }
             _ => {
                 debug!("prctl cmd number: {}", option);
                 return_errno_with_message!(Errno::EINVAL, "unsupported prctl command");
@@ -2,11 +2,15 @@
 
 use core::time::Duration;
 
-use super::{select::do_sys_select, SyscallReturn};
+use super::{
+    poll::{write_back_remaining, RemainingTimeout},
+    select::do_sys_select,
+    SyscallReturn,
+};
 use crate::{
     fs::file_table::FileDesc,
     prelude::*,
-    process::signal::{sig_mask::SigMask, with_sigmask_changed},
+    process::signal::{sig_mask::SigMask, with_sigmask_replaced},
     time::timespec_t,
 };
 
@@ -27,6 +31,10 @@ pub fn sys_pselect6(
     } else {
         None
     };
+    // Like Linux, a zero timeout is not written back.
+    let remaining = timeout
+        .filter(|timeout| !timeout.is_zero())
+        .map(RemainingTimeout::new);
 
     let operate = || {
         do_sys_select(
@@ -39,15 +47,19 @@ pub fn sys_pselect6(
         )
     };
 
-    if sigmask_addr != 0 {
+    let res = if sigmask_addr != 0 {
         let sigmask_with_size = user_space.read_val::<SigMaskWithSize>(sigmask_addr)?;
         if !sigmask_with_size.is_valid() {
             return_errno_with_message!(Errno::EINVAL, "sigmask size is invalid")
         }
-        with_sigmask_changed(ctx, |_: SigMask| sigmask_with_size.sigmask, operate)
+        with_sigmask_replaced(ctx, sigmask_with_size.sigmask, operate)
     } else {
         operate()
-    }
+    };
+
+    write_back_remaining(res, remaining.as_ref(), |remaining| {
+        user_space.write_val(timespec_addr, &timespec_t::from(remaining))
+    })
 }
 
 #[repr(C)]
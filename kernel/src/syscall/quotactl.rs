@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{setxattr::is_capable, SyscallReturn};
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        fs_resolver::FsPath,
+        quota::{Dquot, DquotFields, Quota, QuotaInfo, QuotaInfoFields, QuotaType},
+        utils::{FileSystem, PATH_MAX},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, Gid, Uid},
+};
+
+pub fn sys_quotactl(
+    cmd: u32,
+    special_ptr: Vaddr,
+    id: u32,
+    addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let (subcmd, type_) = parse_cmd(cmd)?;
+    debug!(
+        "subcmd = {:#x}, type = {:?}, special_ptr = {:#x}, id = {}, addr = {:#x}",
+        subcmd, type_, special_ptr, id, addr
+    );
+
+    if special_ptr == 0 {
+        if subcmd != Q_SYNC {
+            return_errno_with_message!(Errno::EFAULT, "the special file is not specified");
+        }
+        // Like `sync`, the file systems are written back along with their quotas.
+        crate::fs::rootfs::root_mount().sync()?;
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    // Since the block devices are not exposed as device files, the file system is identified by
+    // a file in it instead.
+    let fs = {
+        let special = ctx.user_space().read_cstring(special_ptr, PATH_MAX)?;
+        let special = special.to_string_lossy();
+        let fs_path = FsPath::try_from(special.as_ref())?;
+        ctx.posix_thread
+            .fs()
+            .resolver()
+            .read()
+            .lookup(&fs_path)?
+            .fs()
+    };
+
+    do_quotactl(&fs, subcmd, type_, id, addr, true, ctx)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_quotactl_fd(
+    fd: FileDesc,
+    cmd: u32,
+    id: u32,
+    addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let (subcmd, type_) = parse_cmd(cmd)?;
+    debug!(
+        "fd = {}, subcmd = {:#x}, type = {:?}, id = {}, addr = {:#x}",
+        fd, subcmd, type_, id, addr
+    );
+
+    let fs = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        let file = get_file_fast!(&mut file_table, fd);
+        file.as_inode_or_err()?.dentry().fs()
+    };
+
+    // Like Linux, the quota files cannot be specified with `quotactl_fd`.
+    do_quotactl(&fs, subcmd, type_, id, addr, false, ctx)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn parse_cmd(cmd: u32) -> Result<(u32, QuotaType)> {
+    let type_ = QuotaType::try_from(cmd & SUBCMD_MASK)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the quota type is invalid"))?;
+    Ok((cmd >> SUBCMD_SHIFT, type_))
+}
+
+fn do_quotactl(
+    fs: &Arc<dyn FileSystem>,
+    subcmd: u32,
+    type_: QuotaType,
+    id: u32,
+    addr: Vaddr,
+    allows_quota_file: bool,
+    ctx: &Context,
+) -> Result<()> {
+    check_permission(subcmd, type_, id, ctx)?;
+
+    let quota = fs.quota().ok_or_else(|| {
+        Error::with_message(Errno::ENOSYS, "the file system does not support quotas")
+    })?;
+    let user_space = ctx.user_space();
+
+    match subcmd {
+        Q_SYNC => quota.sync()?,
+        Q_QUOTAON => {
+            if !allows_quota_file {
+                return_errno_with_message!(Errno::EINVAL, "the quota file cannot be specified");
+            }
+            quota_on(fs, quota, type_, id, addr, ctx)?;
+        }
+        Q_QUOTAOFF => quota.quota_off(type_)?,
+        Q_GETFMT => user_space.write_val(addr, &quota.format(type_)?)?,
+        Q_GETINFO => {
+            let info = quota.info(type_)?;
+            user_space.write_val(addr, &IfDqinfo::from(&info))?;
+        }
+        Q_SETINFO => {
+            let if_dqinfo = user_space.read_val::<IfDqinfo>(addr)?;
+            let fields = QuotaInfoFields::from_bits_truncate(if_dqinfo.dqi_valid);
+            quota.set_info(type_, &QuotaInfo::from(&if_dqinfo), fields)?;
+        }
+        Q_GETQUOTA => {
+            let dquot = quota.dquot(type_, id)?;
+            user_space.write_val(addr, &IfDqblk::from(&dquot))?;
+        }
+        Q_GETNEXTQUOTA => {
+            let (next_id, dquot) = quota.next_dquot(type_, id)?;
+            let if_dqblk = IfDqblk::from(&dquot);
+            let if_nextdqblk = IfNextDqblk {
+                dqb_bhardlimit: if_dqblk.dqb_bhardlimit,
+                dqb_bsoftlimit: if_dqblk.dqb_bsoftlimit,
+                dqb_curspace: if_dqblk.dqb_curspace,
+                dqb_ihardlimit: if_dqblk.dqb_ihardlimit,
+                dqb_isoftlimit: if_dqblk.dqb_isoftlimit,
+                dqb_curinodes: if_dqblk.dqb_curinodes,
+                dqb_btime: if_dqblk.dqb_btime,
+                dqb_itime: if_dqblk.dqb_itime,
+                dqb_valid: if_dqblk.dqb_valid,
+                dqb_id: next_id,
+            };
+            user_space.write_val(addr, &if_nextdqblk)?;
+        }
+        Q_SETQUOTA => {
+            let if_dqblk = user_space.read_val::<IfDqblk>(addr)?;
+            let fields = DquotFields::from_bits_truncate(if_dqblk.dqb_valid);
+            quota.set_dquot(type_, id, &Dquot::try_from(&if_dqblk)?, fields)?;
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the quota command is invalid"),
+    }
+
+    Ok(())
+}
+
+fn check_permission(subcmd: u32, type_: QuotaType, id: u32, ctx: &Context) -> Result<()> {
+    match subcmd {
+        Q_GETFMT | Q_SYNC | Q_GETINFO => return Ok(()),
+        // The quotas of the owners that the current thread belongs to can be queried.
+        Q_GETQUOTA => {
+            let credentials = ctx.posix_thread.credentials();
+            let is_owner = match type_ {
+                QuotaType::User => credentials.euid() == Uid::new(id),
+                QuotaType::Group => {
+                    credentials.egid() == Gid::new(id)
+                        || credentials.groups().contains(&Gid::new(id))
+                }
+                QuotaType::Project => false,
+            };
+            if is_owner {
+                return Ok(());
+            }
+        }
+        _ => (),
+    }
+
+    if !is_capable(CapSet::SYS_ADMIN, ctx) {
+        return_errno_with_message!(Errno::EPERM, "the quota command requires CAP_SYS_ADMIN");
+    }
+    Ok(())
+}
+
+fn quota_on(
+    fs: &Arc<dyn FileSystem>,
+    quota: &Quota,
+    type_: QuotaType,
+    format: u32,
+    addr: Vaddr,
+    ctx: &Context,
+) -> Result<()> {
+    let dentry = {
+        let path = ctx.user_space().read_cstring(addr, PATH_MAX)?;
+        let path = path.to_string_lossy();
+        let fs_path = FsPath::try_from(path.as_ref())?;
+        ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
+    };
+    if !Arc::ptr_eq(&dentry.fs(), fs) {
+        return_errno_with_message!(Errno::EXDEV, "the quota file is not in the file system");
+    }
+
+    quota.quota_on(type_, format, dentry.inode().clone())
+}
+
+const SUBCMD_SHIFT: u32 = 8;
+const SUBCMD_MASK: u32 = 0xff;
+
+const Q_SYNC: u32 = 0x800001;
+const Q_QUOTAON: u32 = 0x800002;
+const Q_QUOTAOFF: u32 = 0x800003;
+const Q_GETFMT: u32 = 0x800004;
+const Q_GETINFO: u32 = 0x800005;
+const Q_SETINFO: u32 = 0x800006;
+const Q_GETQUOTA: u32 = 0x800007;
+const Q_SETQUOTA: u32 = 0x800008;
+const Q_GETNEXTQUOTA: u32 = 0x800009;
+
+/// The size unit of the block limits in [`IfDqblk`].
+const QIF_DQBLKSIZE: u64 = 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct IfDqblk {
+    dqb_bhardlimit: u64,
+    dqb_bsoftlimit: u64,
+    dqb_curspace: u64,
+    dqb_ihardlimit: u64,
+    dqb_isoftlimit: u64,
+    dqb_curinodes: u64,
+    dqb_btime: u64,
+    dqb_itime: u64,
+    dqb_valid: u32,
+    _padding: u32,
+}
+
+impl From<&Dquot> for IfDqblk {
+    fn from(dquot: &Dquot) -> Self {
+        Self {
+            dqb_bhardlimit: dquot.bhardlimit.div_ceil(QIF_DQBLKSIZE),
+            dqb_bsoftlimit: dquot.bsoftlimit.div_ceil(QIF_DQBLKSIZE),
+            dqb_curspace: dquot.curspace,
+            dqb_ihardlimit: dquot.ihardlimit,
+            dqb_isoftlimit: dquot.isoftlimit,
+            dqb_curinodes: dquot.curinodes,
+            dqb_btime: dquot.btime,
+            dqb_itime: dquot.itime,
+            dqb_valid: DquotFields::all().bits(),
+            _padding: 0,
+        }
+    }
+}
+
+impl TryFrom<&IfDqblk> for Dquot {
+    type Error = Error;
+
+    fn try_from(if_dqblk: &IfDqblk) -> Result<Self> {
+        let to_bytes = |limit: u64| {
+            limit
+                .checked_mul(QIF_DQBLKSIZE)
+                .ok_or_else(|| Error::with_message(Errno::ERANGE, "the space limit is too large"))
+        };
+
+        Ok(Self {
+            bhardlimit: to_bytes(if_dqblk.dqb_bhardlimit)?,
+            bsoftlimit: to_bytes(if_dqblk.dqb_bsoftlimit)?,
+            curspace: if_dqblk.dqb_curspace,
+            ihardlimit: if_dqblk.dqb_ihardlimit,
+            isoftlimit: if_dqblk.dqb_isoftlimit,
+            curinodes: if_dqblk.dqb_curinodes,
+            btime: if_dqblk.dqb_btime,
+            itime: if_dqblk.dqb_itime,
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct IfNextDqblk {
+    dqb_bhardlimit: u64,
+    dqb_bsoftlimit: u64,
+    dqb_curspace: u64,
+    dqb_ihardlimit: u64,
+    dqb_isoftlimit: u64,
+    dqb_curinodes: u64,
+    dqb_btime: u64,
+    dqb_itime: u64,
+    dqb_valid: u32,
+    dqb_id: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct IfDqinfo {
+    dqi_bgrace: u64,
+    dqi_igrace: u64,
+    dqi_flags: u32,
+    dqi_valid: u32,
+}
+
+impl From<&QuotaInfo> for IfDqinfo {
+    fn from(info: &QuotaInfo) -> Self {
+        Self {
+            dqi_bgrace: info.bgrace,
+            dqi_igrace: info.igrace,
+            dqi_flags: info.flags,
+            dqi_valid: QuotaInfoFields::all().bits(),
+        }
+    }
+}
+
+impl From<&IfDqinfo> for QuotaInfo {
+    fn from(if_dqinfo: &IfDqinfo) -> Self {
+        Self {
+            bgrace: if_dqinfo.dqi_bgrace,
+            igrace: if_dqinfo.dqi_igrace,
+            flags: if_dqinfo.dqi_flags,
+        }
+    }
+}
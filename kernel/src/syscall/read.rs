@@ -2,7 +2,10 @@
 
 use super::SyscallReturn;
 use crate::{
-    fs::file_table::{get_file_fast, FileDesc},
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        notify::fanotify::FanotifyFile,
+    },
     prelude::*,
 };
 
@@ -20,6 +23,23 @@ pub fn sys_read(
     let mut file_table = ctx.thread_local.borrow_file_table_mut();
     let file = get_file_fast!(&mut file_table, fd);
 
+    // Reading fanotify events installs new file descriptors, so the file table must be released.
+    if file.downcast_ref::<FanotifyFile>().is_some() {
+        let file = file.into_owned();
+        drop(file_table);
+        let fanotify_file = file.downcast_ref::<FanotifyFile>().unwrap();
+        let user_space = ctx.user_space();
+        let mut writer = user_space.writer(user_buf_addr, buf_len)?;
+        let read_len =
+            fanotify_file
+                .read_events(&mut writer, ctx)
+                .map_err(|err| match err.error() {
+                    Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+                    _ => err,
+                })?;
+        return Ok(SyscallReturn::Return(read_len as _));
+    }
+
     // According to <https://man7.org/linux/man-pages/man2/read.2.html>, if
     // the user specified an empty buffer, we should detect errors by checking
     // the file descriptor. If no errors detected, return 0 successfully.
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    mem::{offset_of, size_of},
+    time::Duration,
+};
+
+use super::{recvmsg::recv_msg, sendmmsg::MAX_NR_MSGS, SyscallReturn};
+use crate::{
+    fs::file_table::{get_file_fast, FileDesc},
+    net::socket::SendRecvFlags,
+    prelude::*,
+    time::{clocks::MonotonicClock, timespec_t},
+    util::net::CUserMMsgHdr,
+};
+
+pub fn sys_recvmmsg(
+    sockfd: FileDesc,
+    user_mmsghdr_ptr: Vaddr,
+    vlen: u32,
+    flags: i32,
+    timeout_ptr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let mut flags = SendRecvFlags::from_bits_truncate(flags);
+    let user_space = ctx.user_space();
+    let timeout = if timeout_ptr != 0 {
+        let timespec = user_space.read_val::<timespec_t>(timeout_ptr)?;
+        Some(Duration::try_from(timespec)?)
+    } else {
+        None
+    };
+    debug!(
+        "sockfd = {}, user_mmsghdr_ptr = {:#x}, vlen = {}, flags = {:?}, timeout = {:?}",
+        sockfd, user_mmsghdr_ptr, vlen, flags, timeout
+    );
+
+    // The file table cannot be kept borrowed because it will be accessed again to pass files
+    // with `SCM_RIGHTS`.
+    let file = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        get_file_fast!(&mut file_table, sockfd).into_owned()
+    };
+    let socket = file.as_socket_or_err()?;
+
+    let wait_for_one = flags.contains(SendRecvFlags::MSG_WAITFORONE);
+    flags.remove(SendRecvFlags::MSG_WAITFORONE);
+
+    let clock = MonotonicClock::get();
+    let deadline = timeout.map(|timeout| clock.read_time() + timeout);
+
+    let mut nr_received = 0;
+    for i in 0..vlen.min(MAX_NR_MSGS) as usize {
+        let mmsghdr_ptr = user_mmsghdr_ptr + i * size_of::<CUserMMsgHdr>();
+
+        let res = user_space
+            .read_val::<CUserMMsgHdr>(mmsghdr_ptr)
+            .and_then(|c_user_mmsghdr| {
+                recv_msg(
+                    socket,
+                    &c_user_mmsghdr.msg_hdr,
+                    mmsghdr_ptr + offset_of!(CUserMMsgHdr, msg_hdr),
+                    flags,
+                    ctx,
+                )
+            })
+            .and_then(|received_bytes| {
+                user_space.write_val(
+                    mmsghdr_ptr + offset_of!(CUserMMsgHdr, msg_len),
+                    &(received_bytes as u32),
+                )
+            });
+        match res {
+            Ok(()) => nr_received += 1,
+            // Like Linux, the error is reported only if no messages have been received.
+            // Otherwise, the number of the received messages is returned.
+            Err(err) if nr_received == 0 => return Err(err),
+            Err(_) => break,
+        }
+
+        if wait_for_one {
+            flags.insert(SendRecvFlags::MSG_DONTWAIT);
+        }
+        // Like Linux, the timeout is only checked after a message is received.
+        if deadline.is_some_and(|deadline| clock.read_time() >= deadline) {
+            break;
+        }
+    }
+
+    // Like Linux, the remaining time is written back.
+    if let Some(deadline) = deadline {
+        let remaining = deadline.saturating_sub(clock.read_time());
+        user_space.write_val(timeout_ptr, &timespec_t::from(remaining))?;
+    }
+
+    Ok(SyscallReturn::Return(nr_received as _))
+}
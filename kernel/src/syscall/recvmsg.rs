@@ -1,9 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::mem::offset_of;
+
 use super::SyscallReturn;
 use crate::{
     fs::file_table::{get_file_fast, FileDesc},
-    net::socket::SendRecvFlags,
+    net::socket::{SendRecvFlags, Socket},
     prelude::*,
     util::net::CUserMsgHdr,
 };
@@ -22,10 +24,30 @@ pub fn sys_recvmsg(
         sockfd, c_user_msghdr, flags
     );
 
-    let mut file_table = ctx.thread_local.borrow_file_table_mut();
-    let file = get_file_fast!(&mut file_table, sockfd);
+    // The file table cannot be kept borrowed because it will be accessed again to pass files
+    // with `SCM_RIGHTS`.
+    let file = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        get_file_fast!(&mut file_table, sockfd).into_owned()
+    };
     let socket = file.as_socket_or_err()?;
 
+    let total_bytes = recv_msg(socket, &c_user_msghdr, user_msghdr_ptr, flags, ctx)?;
+
+    Ok(SyscallReturn::Return(total_bytes as _))
+}
+
+/// Receives a message into the buffers described by the user message header.
+///
+/// `user_msghdr_ptr` is the address of the user message header, where the length of the control
+/// messages and the flags of the received message will be written back.
+pub(super) fn recv_msg(
+    socket: &dyn Socket,
+    c_user_msghdr: &CUserMsgHdr,
+    user_msghdr_ptr: Vaddr,
+    flags: SendRecvFlags,
+    ctx: &Context,
+) -> Result<usize> {
     let (total_bytes, message_header) = {
         let user_space = ctx.user_space();
         let mut io_vec_writer = c_user_msghdr.copy_writer_array_from_user(&user_space)?;
@@ -42,9 +64,33 @@ pub fn sys_recvmsg(
         c_user_msghdr.write_socket_addr_to_user(addr)?;
     }
 
-    if c_user_msghdr.msg_control != 0 {
-        warn!("receiving control message is not supported");
+    let mut msg_flags = SendRecvFlags::empty();
+    if message_header.is_from_error_queue() {
+        msg_flags |= SendRecvFlags::MSG_ERRQUEUE;
+    }
+    if message_header.is_truncated() {
+        msg_flags |= SendRecvFlags::MSG_TRUNC;
     }
 
-    Ok(SyscallReturn::Return(total_bytes as _))
+    let (control_len, is_control_truncated) = c_user_msghdr.write_control_messages_to_user(
+        message_header.into_control_messages(),
+        flags,
+        ctx,
+    )?;
+
+    if is_control_truncated {
+        msg_flags |= SendRecvFlags::MSG_CTRUNC;
+    }
+
+    let user_space = ctx.user_space();
+    user_space.write_val(
+        user_msghdr_ptr + offset_of!(CUserMsgHdr, msg_controllen),
+        &control_len,
+    )?;
+    user_space.write_val(
+        user_msghdr_ptr + offset_of!(CUserMsgHdr, msg_flags),
+        &(msg_flags.bits() as u32),
+    )?;
+
+    Ok(total_bytes)
 }
@@ -2,8 +2,8 @@
 
 use super::{
     setxattr::{
-        check_xattr_namespace, lookup_dentry_for_xattr, parse_xattr_name,
-        read_xattr_name_cstr_from_user, XattrFileCtx,
+        check_xattr_permission, lookup_dentry_for_xattr, parse_xattr_name, read_xattr_file_ctx_at,
+        read_xattr_name_cstr_from_user, XattrAccess, XattrFileCtx,
     },
     SyscallReturn,
 };
@@ -41,6 +41,21 @@ pub fn sys_fremovexattr(fd: FileDesc, name_ptr: Vaddr, ctx: &Context) -> Result<
     Ok(SyscallReturn::Return(0))
 }
 
+pub fn sys_removexattrat(
+    dirfd: FileDesc,
+    path_ptr: Vaddr,
+    at_flags: u32,
+    name_ptr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let file_ctx = read_xattr_file_ctx_at(dirfd, path_ptr, at_flags, &user_space)?;
+
+    removexattr(file_ctx, name_ptr, &user_space, ctx)?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
 fn removexattr(
     file_ctx: XattrFileCtx,
     name_ptr: Vaddr,
@@ -50,8 +65,8 @@ fn removexattr(
     let name_cstr = read_xattr_name_cstr_from_user(name_ptr, user_space)?;
     let name_str = name_cstr.to_string_lossy();
     let xattr_name = parse_xattr_name(name_str.as_ref())?;
-    check_xattr_namespace(xattr_name.namespace(), ctx)?;
 
     let dentry = lookup_dentry_for_xattr(&file_ctx, ctx)?;
+    check_xattr_permission(&xattr_name, &dentry, XattrAccess::Write, ctx)?;
     dentry.remove_xattr(xattr_name)
 }
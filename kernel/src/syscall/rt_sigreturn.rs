@@ -5,7 +5,15 @@ use core::sync::atomic::Ordering;
 use ostd::{cpu::context::UserContext, user::UserContextApi};
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::signal::c_types::ucontext_t};
+use crate::{
+    prelude::*,
+    process::signal::{
+        c_types::ucontext_t,
+        constants::{SIGKILL, SIGSTOP},
+        sig_mask::SigMask,
+        SignalContext,
+    },
+};
 
 pub fn sys_rt_sigreturn(ctx: &Context, user_ctx: &mut UserContext) -> Result<SyscallReturn> {
     let Context {
@@ -25,7 +33,16 @@ pub fn sys_rt_sigreturn(ctx: &Context, user_ctx: &mut UserContext) -> Result<Sys
     // However, for most glibc applications, the restorer codes is provided by glibc and RESTORER flag is set.
     debug_assert!(sig_context_addr == user_ctx.stack_pointer() as Vaddr);
 
-    let ucontext = ctx.user_space().read_val::<ucontext_t>(sig_context_addr)?;
+    let user_space = ctx.user_space();
+    let ucontext = user_space.read_val::<ucontext_t>(sig_context_addr)?;
+
+    // Restore the FPU state first, since it is the only part that can be rejected after the
+    // `ucontext_t` is read. No thread state is changed if it fails.
+    let fpstate_addr = ucontext.uc_mcontext.inner.fpregs;
+    if fpstate_addr != 0 {
+        user_ctx.restore_fpstate(fpstate_addr, &user_space)?;
+    }
+    drop(user_space);
 
     // If the sig stack is active and used by current handler, decrease handler counter.
     if let Some(sig_stack) = &mut *thread_local.sig_stack().borrow_mut() {
@@ -36,23 +53,21 @@ pub fn sys_rt_sigreturn(ctx: &Context, user_ctx: &mut UserContext) -> Result<Sys
     }
 
     // Set previous ucontext address
-    if ucontext.uc_link == 0 {
-        thread_local.sig_context().set(None);
-    } else {
-        thread_local.sig_context().set(Some(ucontext.uc_link));
-    };
+    thread_local
+        .sig_context()
+        .set(Some(ucontext.uc_link).filter(|addr| *addr != 0));
     ucontext
         .uc_mcontext
         .inner
         .gp_regs
         .copy_to_raw(user_ctx.general_regs_mut());
 
-    // unblock sig mask
-    let sig_mask = ucontext.uc_sigmask;
-    let old_mask = posix_thread.sig_mask().load(Ordering::Relaxed);
-    posix_thread
-        .sig_mask()
-        .store(old_mask - sig_mask, Ordering::Relaxed);
+    // Restore the signal mask before the signal handler is invoked. Like `rt_sigprocmask`,
+    // `SIGKILL` and `SIGSTOP` cannot be blocked.
+    let mut sig_mask = SigMask::from(ucontext.uc_sigmask);
+    sig_mask -= SIGKILL;
+    sig_mask -= SIGSTOP;
+    posix_thread.sig_mask().store(sig_mask, Ordering::Relaxed);
 
     Ok(SyscallReturn::NoReturn)
 }
@@ -8,7 +8,7 @@ use crate::{
     process::signal::{
         constants::{SIGKILL, SIGSTOP},
         sig_mask::SigMask,
-        with_sigmask_changed,
+        with_sigmask_replaced,
     },
 };
 
@@ -35,13 +35,14 @@ pub fn sys_rt_sigsuspend(
         mask
     };
 
-    // Wait until receiving any signal
+    // Wait until receiving any signal. Like Linux, the syscall is restarted if no signal handler
+    // is invoked.
     let waiter = Waiter::new_pair().0;
-    with_sigmask_changed(
-        ctx,
-        |old_mask| old_mask + sigmask,
-        || waiter.pause_until(|| None::<()>),
-    )?;
+    with_sigmask_replaced(ctx, sigmask, || {
+        waiter
+            .pause_until(|| None::<()>)
+            .map_err(|_| Error::new(Errno::ERESTARTNOHAND))
+    })?;
 
     // This syscall should always return `Err(EINTR)`. This path should never be reached.
     unreachable!("rt_sigsuspend always return EINTR");
@@ -1,14 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{sched_getattr::access_sched_attr_with, SyscallReturn};
-use crate::{prelude::*, sched::SchedPolicy, thread::Tid};
+use super::{
+    sched_getattr::{access_sched_attr_with, LinuxSchedAttr},
+    SyscallReturn,
+};
+use crate::{prelude::*, thread::Tid};
 
 pub fn sys_sched_getparam(tid: Tid, addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
     let policy = access_sched_attr_with(tid, ctx, |attr| Ok(attr.policy()))?;
-    let rt_prio = match policy {
-        SchedPolicy::RealTime { rt_prio, .. } => rt_prio.get().into(),
-        _ => 0,
-    };
+    let rt_prio = LinuxSchedAttr::try_from(policy)?.sched_priority;
 
     let space = ctx.user_space();
     space
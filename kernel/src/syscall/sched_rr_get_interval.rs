@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{sched_getattr::access_sched_attr_with, SyscallReturn};
+use crate::{prelude::*, thread::Tid, time::timespec_t};
+
+pub fn sys_sched_rr_get_interval(
+    tid: Tid,
+    interval_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let time_slice = access_sched_attr_with(tid, ctx, |attr| Ok(attr.time_slice()))?;
+    debug!("tid = {}, time_slice = {:?}", tid, time_slice);
+
+    let interval = timespec_t::from(time_slice);
+    ctx.user_space().write_val(interval_addr, &interval)?;
+
+    Ok(SyscallReturn::Return(0))
+}
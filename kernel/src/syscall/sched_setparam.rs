@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{sched_getattr::access_sched_attr_with, SyscallReturn};
+use super::{
+    sched_get_priority_max::static_to_rt, sched_getattr::access_sched_attr_with, SyscallReturn,
+};
 use crate::{prelude::*, sched::SchedPolicy, thread::Tid};
 
 pub fn sys_sched_setparam(tid: Tid, addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
@@ -9,19 +11,26 @@ pub fn sys_sched_setparam(tid: Tid, addr: Vaddr, ctx: &Context) -> Result<Syscal
         .read_val(addr)
         .map_err(|_| Error::new(Errno::EINVAL))?;
 
-    let update = |policy: &mut SchedPolicy| {
-        match policy {
-            SchedPolicy::RealTime { rt_prio, .. } => {
-                *rt_prio = u8::try_from(prio)?
-                    .try_into()
-                    .map_err(|msg| Error::with_message(Errno::EINVAL, msg))?;
+    access_sched_attr_with(tid, ctx, |attr| {
+        // The policy is set as a whole, so that the scheduling classes see the
+        // new priority.
+        let policy = match attr.policy() {
+            SchedPolicy::RealTime { rt_policy, .. } => SchedPolicy::RealTime {
+                rt_prio: static_to_rt(
+                    u32::try_from(prio)
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid priority"))?,
+                )?,
+                rt_policy,
+            },
+            SchedPolicy::Stop => {
+                return_errno_with_message!(Errno::EPERM, "the priority of stop threads is fixed")
             }
-            _ if prio != 0 => return Err(Error::with_message(Errno::EINVAL, "invalid priority")),
-            _ => {}
-        }
+            _ if prio != 0 => return_errno_with_message!(Errno::EINVAL, "invalid priority"),
+            policy => policy,
+        };
+        attr.set_policy(policy);
         Ok(())
-    };
-    access_sched_attr_with(tid, ctx, |attr| attr.update_policy(update))?;
+    })?;
 
     Ok(SyscallReturn::Return(0))
 }
@@ -3,7 +3,7 @@
 use core::time::Duration;
 
 use super::{
-    poll::{do_poll, PollFd},
+    poll::{do_poll, write_back_remaining, PollFd, RemainingTimeout},
     SyscallReturn,
 };
 use crate::{events::IoEvents, fs::file_table::FileDesc, prelude::*, time::timeval_t};
@@ -25,15 +25,24 @@ pub fn sys_select(
             .normalize();
         Some(Duration::try_from(timeval)?)
     };
+    // Like Linux, a zero timeout is not written back.
+    let remaining = timeout
+        .filter(|timeout| !timeout.is_zero())
+        .map(RemainingTimeout::new);
 
-    do_sys_select(
+    let res = do_sys_select(
         nfds,
         readfds_addr,
         writefds_addr,
         exceptfds_addr,
         timeout,
         ctx,
-    )
+    );
+
+    write_back_remaining(res, remaining.as_ref(), |remaining| {
+        ctx.user_space()
+            .write_val(timeval_addr, &timeval_t::from(remaining))
+    })
 }
 
 pub fn do_sys_select(
@@ -76,11 +85,6 @@ pub fn do_sys_select(
         ctx,
     )?;
 
-    // FIXME: The Linux select() and pselect6() system call
-    // modifies its timeout argument to reflect the amount of time not slept.
-    // However, the glibc wrapper function hides this behavior.
-    // Maybe we should follow the Linux behavior.
-
     let set_fdset = |fdset_addr: Vaddr, fdset: Option<FdSet>| -> Result<()> {
         if let Some(fdset) = fdset {
             debug_assert!(fdset_addr != 0);
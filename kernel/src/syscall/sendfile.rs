@@ -1,9 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
+use ostd::mm::UntypedMem;
+
+use super::{splice::splice_file_to_pipe, SyscallReturn};
 use crate::{
-    fs::file_table::{FileDesc, WithFileTable},
+    fs::{
+        file_handle::FileLike,
+        file_table::{FileDesc, WithFileTable},
+        inode_handle::InodeHandle,
+        pipe::PipeWriter,
+        utils::{InodeType, SeekFrom, StatusFlags},
+    },
     prelude::*,
+    vm::vmo::CommitFlags,
 };
 
 pub fn sys_sendfile(
@@ -52,10 +61,104 @@ pub fn sys_sendfile(
         count = MAX_COUNT;
     }
 
+    let mut offset = offset.map(|offset| offset as usize);
+
+    let total_len = if let Some(pipe_out) = out_file.downcast_ref::<PipeWriter>() {
+        splice_file_to_pipe(&*in_file, offset.as_mut(), pipe_out, count, false)?
+    } else if let Some(in_handle) = in_file
+        .downcast_ref::<InodeHandle>()
+        .filter(|handle| is_page_cache_backed(handle))
+    {
+        send_from_page_cache(in_handle, offset.as_mut(), &*out_file, count)?
+    } else {
+        send_with_buffer(&*in_file, offset.as_mut(), &*out_file, count)?
+    };
+
+    if let Some(offset) = offset {
+        ctx.user_space().write_val(offset_ptr, &(offset as isize))?;
+    }
+
+    Ok(SyscallReturn::Return(total_len as _))
+}
+
+fn is_page_cache_backed(handle: &InodeHandle) -> bool {
+    let inode = handle.dentry().inode();
+    inode.type_() == InodeType::File
+        && inode.page_cache().is_some()
+        && !handle.status_flags().contains(StatusFlags::O_DIRECT)
+}
+
+/// Sends the data from the page cache of `in_handle` to `out_file`.
+///
+/// The pages are read directly by `out_file` (e.g., copied to the send buffer of a socket),
+/// so there is no intermediate buffer.
+fn send_from_page_cache(
+    in_handle: &InodeHandle,
+    offset: Option<&mut usize>,
+    out_file: &dyn FileLike,
+    count: usize,
+) -> Result<usize> {
+    let inode = in_handle.dentry().inode();
+    let page_cache = inode.page_cache().unwrap();
+
+    let start = match offset.as_deref() {
+        Some(offset) => *offset,
+        None => in_handle.offset(),
+    };
+    let end = inode.size().min(start.saturating_add(count));
+
+    let mut pos = start;
+    while pos < end {
+        let page_offset = pos % PAGE_SIZE;
+        let chunk_len = (PAGE_SIZE - page_offset).min(end - pos);
+
+        let write_res = page_cache
+            .commit_on(pos / PAGE_SIZE, CommitFlags::empty())
+            .and_then(|frame| {
+                let mut reader = frame.reader();
+                reader.skip(page_offset).limit(chunk_len);
+                out_file.write(&mut reader.to_fallible())
+            });
+
+        match write_res {
+            Ok(len) => {
+                pos += len;
+                if len < chunk_len {
+                    break;
+                }
+            }
+            Err(e) => {
+                if pos > start {
+                    warn!("error occurs when trying to send file: {:?}", e);
+                    break;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    // Like reading with `read_at` and `read`, the file offset is only changed if no offset is
+    // given.
+    match offset {
+        Some(offset) => *offset = pos,
+        None => {
+            in_handle.seek(SeekFrom::Start(pos))?;
+        }
+    }
+
+    Ok(pos - start)
+}
+
+/// Sends the data from `in_file` to `out_file` via an intermediate buffer.
+fn send_with_buffer(
+    in_file: &dyn FileLike,
+    mut offset: Option<&mut usize>,
+    out_file: &dyn FileLike,
+    count: usize,
+) -> Result<usize> {
     const BUFFER_SIZE: usize = PAGE_SIZE;
     let mut buffer = vec![0u8; BUFFER_SIZE].into_boxed_slice();
     let mut total_len = 0;
-    let mut offset = offset.map(|offset| offset as usize);
 
     while total_len < count {
         // The offset decides how to read from `in_file`.
@@ -67,7 +170,7 @@ pub fn sys_sendfile(
         let max_readlen = buffer.len().min(count - total_len);
 
         // Read from `in_file`
-        let read_res = if let Some(offset) = offset.as_mut() {
+        let read_res = if let Some(offset) = offset.as_deref_mut() {
             let res = in_file.read_bytes_at(*offset, &mut buffer[..max_readlen]);
             if let Ok(len) = res.as_ref() {
                 *offset += *len;
@@ -113,9 +216,5 @@ pub fn sys_sendfile(
         }
     }
 
-    if let Some(offset) = offset {
-        ctx.user_space().write_val(offset_ptr, &(offset as isize))?;
-    }
-
-    Ok(SyscallReturn::Return(total_len as _))
+    Ok(total_len)
 }
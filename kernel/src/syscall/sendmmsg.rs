@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::{offset_of, size_of};
+
+use super::{sendmsg::send_msg, SyscallReturn};
+use crate::{
+    fs::file_table::{get_file_fast, FileDesc},
+    net::socket::SendRecvFlags,
+    prelude::*,
+    util::net::CUserMMsgHdr,
+};
+
+/// The maximum number of messages in a batch, which is the same as Linux's `UIO_MAXIOV`.
+pub(super) const MAX_NR_MSGS: u32 = 1024;
+
+pub fn sys_sendmmsg(
+    sockfd: FileDesc,
+    user_mmsghdr_ptr: Vaddr,
+    vlen: u32,
+    flags: i32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SendRecvFlags::from_bits_truncate(flags);
+    debug!(
+        "sockfd = {}, user_mmsghdr_ptr = {:#x}, vlen = {}, flags = {:?}",
+        sockfd, user_mmsghdr_ptr, vlen, flags
+    );
+
+    // The file table cannot be kept borrowed because it will be accessed again to pass files
+    // with `SCM_RIGHTS`.
+    let file = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        get_file_fast!(&mut file_table, sockfd).into_owned()
+    };
+    let socket = file.as_socket_or_err()?;
+
+    let user_space = ctx.user_space();
+    let mut nr_sent = 0;
+    for i in 0..vlen.min(MAX_NR_MSGS) as usize {
+        let mmsghdr_ptr = user_mmsghdr_ptr + i * size_of::<CUserMMsgHdr>();
+
+        let res = user_space
+            .read_val::<CUserMMsgHdr>(mmsghdr_ptr)
+            .and_then(|c_user_mmsghdr| send_msg(socket, &c_user_mmsghdr.msg_hdr, flags, ctx))
+            .and_then(|sent_bytes| {
+                user_space.write_val(
+                    mmsghdr_ptr + offset_of!(CUserMMsgHdr, msg_len),
+                    &(sent_bytes as u32),
+                )
+            });
+        match res {
+            Ok(()) => nr_sent += 1,
+            // Like Linux, the error is reported only if no messages have been sent. Otherwise, the
+            // number of the sent messages is returned, and the user space can retry the failed
+            // message to see the error.
+            Err(err) if nr_sent == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+
+    Ok(SyscallReturn::Return(nr_sent as _))
+}
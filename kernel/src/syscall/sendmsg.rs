@@ -3,7 +3,7 @@
 use super::SyscallReturn;
 use crate::{
     fs::file_table::{get_file_fast, FileDesc},
-    net::socket::{MessageHeader, SendRecvFlags},
+    net::socket::{MessageHeader, SendRecvFlags, Socket},
     prelude::*,
     util::net::CUserMsgHdr,
 };
@@ -22,33 +22,41 @@ pub fn sys_sendmsg(
         sockfd, c_user_msghdr, flags
     );
 
-    let mut file_table = ctx.thread_local.borrow_file_table_mut();
-    let file = get_file_fast!(&mut file_table, sockfd);
+    // The file table cannot be kept borrowed because it will be accessed again to pass files
+    // with `SCM_RIGHTS`.
+    let file = {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        get_file_fast!(&mut file_table, sockfd).into_owned()
+    };
     let socket = file.as_socket_or_err()?;
 
+    let total_bytes = send_msg(socket, &c_user_msghdr, flags, ctx)?;
+
+    Ok(SyscallReturn::Return(total_bytes as _))
+}
+
+/// Sends a message described by the user message header.
+pub(super) fn send_msg(
+    socket: &dyn Socket,
+    c_user_msghdr: &CUserMsgHdr,
+    flags: SendRecvFlags,
+    ctx: &Context,
+) -> Result<usize> {
     let user_space = ctx.user_space();
     let (mut io_vec_reader, message_header) = {
         let addr = c_user_msghdr.read_socket_addr_from_user()?;
         let io_vec_reader = c_user_msghdr.copy_reader_array_from_user(&user_space)?;
 
-        let control_message = {
-            if c_user_msghdr.msg_control != 0 {
-                // TODO: support sending control message
-                warn!("control message is not supported now");
-            }
-            None
-        };
+        let control_messages = c_user_msghdr.read_control_messages_from_user(ctx)?;
 
-        (io_vec_reader, MessageHeader::new(addr, control_message))
+        (io_vec_reader, MessageHeader::new(addr, control_messages))
     };
 
-    let total_bytes = socket
+    socket
         .sendmsg(&mut io_vec_reader, message_header, flags)
         .map_err(|err| match err.error() {
             // FIXME: `sendmsg` should not be restarted if a timeout has been set on the socket using `setsockopt`.
             Errno::EINTR => Error::new(Errno::ERESTARTSYS),
             _ => err,
-        })?;
-
-    Ok(SyscallReturn::Return(total_bytes as _))
+        })
 }
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::Gid};
+use crate::{net::socket::netlink::proc_events, prelude::*, process::Gid};
 
 pub fn sys_setgid(gid: i32, ctx: &Context) -> Result<SyscallReturn> {
     debug!("gid = {}", gid);
@@ -12,8 +12,26 @@ pub fn sys_setgid(gid: i32, ctx: &Context) -> Result<SyscallReturn> {
 
     let gid = Gid::new(gid as u32);
 
+    let old_ids = {
+        let credentials = ctx.posix_thread.credentials();
+        (credentials.rgid(), credentials.egid())
+    };
+
     let credentials = ctx.posix_thread.credentials_mut();
     credentials.set_gid(gid);
 
+    let credentials = ctx.posix_thread.credentials();
+    ctx.process.update_vdso_identity(&credentials);
+
+    let new_ids = (credentials.rgid(), credentials.egid());
+    if new_ids != old_ids {
+        proc_events::on_gid_change(
+            ctx.posix_thread.tid(),
+            ctx.process.pid(),
+            new_ids.0,
+            new_ids.1,
+        );
+    }
+
     Ok(SyscallReturn::Return(0))
 }
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::Gid};
+use crate::{net::socket::netlink::proc_events, prelude::*, process::Gid};
 
 pub fn sys_setregid(rgid: i32, egid: i32, ctx: &Context) -> Result<SyscallReturn> {
     debug!("rgid = {}, egid = {}", rgid, egid);
@@ -18,8 +18,26 @@ pub fn sys_setregid(rgid: i32, egid: i32, ctx: &Context) -> Result<SyscallReturn
         None
     };
 
+    let old_ids = {
+        let credentials = ctx.posix_thread.credentials();
+        (credentials.rgid(), credentials.egid())
+    };
+
     let credentials = ctx.posix_thread.credentials_mut();
     credentials.set_regid(rgid, egid)?;
 
+    let credentials = ctx.posix_thread.credentials();
+    ctx.process.update_vdso_identity(&credentials);
+
+    let new_ids = (credentials.rgid(), credentials.egid());
+    if new_ids != old_ids {
+        proc_events::on_gid_change(
+            ctx.posix_thread.tid(),
+            ctx.process.pid(),
+            new_ids.0,
+            new_ids.1,
+        );
+    }
+
     Ok(SyscallReturn::Return(0))
 }
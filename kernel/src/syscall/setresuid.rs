@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::Uid};
+use crate::{net::socket::netlink::proc_events, prelude::*, process::Uid};
 
 pub fn sys_setresuid(ruid: i32, euid: i32, suid: i32, ctx: &Context) -> Result<SyscallReturn> {
     let ruid = if ruid > 0 {
@@ -24,9 +24,26 @@ pub fn sys_setresuid(ruid: i32, euid: i32, suid: i32, ctx: &Context) -> Result<S
 
     debug!("ruid = {:?}, euid = {:?}, suid = {:?}", ruid, euid, suid);
 
-    let credentials = ctx.posix_thread.credentials_mut();
+    let old_ids = {
+        let credentials = ctx.posix_thread.credentials();
+        (credentials.ruid(), credentials.euid())
+    };
 
+    let credentials = ctx.posix_thread.credentials_mut();
     credentials.set_resuid(ruid, euid, suid)?;
 
+    let credentials = ctx.posix_thread.credentials();
+    ctx.process.update_vdso_identity(&credentials);
+
+    let new_ids = (credentials.ruid(), credentials.euid());
+    if new_ids != old_ids {
+        proc_events::on_uid_change(
+            ctx.posix_thread.tid(),
+            ctx.process.pid(),
+            new_ids.0,
+            new_ids.1,
+        );
+    }
+
     Ok(SyscallReturn::Return(0))
 }
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{setxattr::is_capable, SyscallReturn};
+use crate::{
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    time::{clocks::RealTimeClock, timeval_t},
+};
+
+// The use of the timezone structure is obsolete, so it is only checked and then ignored.
+pub fn sys_settimeofday(
+    timeval_addr: Vaddr,
+    timezone_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let time = if timeval_addr != 0 {
+        let timeval = ctx.user_space().read_val::<timeval_t>(timeval_addr)?;
+        debug!("timeval = {:?}", timeval);
+        Some(Duration::try_from(timeval)?)
+    } else {
+        None
+    };
+    if timezone_addr != 0 {
+        ctx.user_space().read_val::<[i32; 2]>(timezone_addr)?;
+    }
+
+    if !is_capable(CapSet::SYS_TIME, ctx) {
+        return_errno_with_message!(Errno::EPERM, "setting the clock requires CAP_SYS_TIME");
+    }
+
+    if let Some(time) = time {
+        RealTimeClock::get().set_time(time);
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
@@ -10,7 +10,8 @@ use crate::{
         fs_resolver::{FsPath, AT_FDCWD},
         path::Dentry,
         utils::{
-            XattrName, XattrNamespace, XattrSetFlags, XATTR_NAME_MAX_LEN, XATTR_VALUE_MAX_LEN,
+            InodeType, XattrName, XattrNamespace, XattrSetFlags, XATTR_NAME_MAX_LEN,
+            XATTR_VALUE_MAX_LEN,
         },
     },
     prelude::*,
@@ -91,6 +92,32 @@ pub fn sys_fsetxattr(
     Ok(SyscallReturn::Return(0))
 }
 
+pub fn sys_setxattrat(
+    dirfd: FileDesc,
+    path_ptr: Vaddr,
+    at_flags: u32,
+    name_ptr: Vaddr,
+    args_ptr: Vaddr,
+    args_size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let file_ctx = read_xattr_file_ctx_at(dirfd, path_ptr, at_flags, &user_space)?;
+    let args = read_xattr_args_from_user(args_ptr, args_size, &user_space)?;
+
+    setxattr(
+        file_ctx,
+        name_ptr,
+        args.value as _,
+        args.size as _,
+        args.flags as _,
+        &user_space,
+        ctx,
+    )?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
 fn setxattr(
     file_ctx: XattrFileCtx,
     name_ptr: Vaddr,
@@ -106,7 +133,6 @@ fn setxattr(
     let name_cstr = read_xattr_name_cstr_from_user(name_ptr, user_space)?;
     let name_str = name_cstr.to_string_lossy();
     let xattr_name = parse_xattr_name(name_str.as_ref())?;
-    check_xattr_namespace(xattr_name.namespace(), ctx)?;
 
     if value_len > XATTR_VALUE_MAX_LEN {
         return_errno_with_message!(Errno::E2BIG, "xattr value too long");
@@ -114,6 +140,7 @@ fn setxattr(
     let mut value_reader = user_space.reader(value_ptr, value_len)?;
 
     let dentry = lookup_dentry_for_xattr(&file_ctx, ctx)?;
+    check_xattr_permission(&xattr_name, &dentry, XattrAccess::Write, ctx)?;
     dentry.set_xattr(xattr_name, &mut value_reader, flags)
 }
 
@@ -121,29 +148,108 @@ fn setxattr(
 pub(super) enum XattrFileCtx<'a> {
     Path(CString),
     PathNoFollow(CString),
+    PathAt(FileDesc, CString, XattrAtFlags),
     FileHandle(Cow<'a, Arc<dyn FileLike>>),
 }
 
+bitflags! {
+    /// The flags of the `*xattrat` system calls.
+    pub(super) struct XattrAtFlags: u32 {
+        const AT_SYMLINK_NOFOLLOW = 0x100;
+        const AT_EMPTY_PATH = 0x1000;
+    }
+}
+
+/// The arguments of the `setxattrat` and `getxattrat` system calls.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct XattrArgs {
+    pub(super) value: u64,
+    pub(super) size: u32,
+    pub(super) flags: u32,
+}
+
+pub(super) fn read_xattr_file_ctx_at(
+    dirfd: FileDesc,
+    path_ptr: Vaddr,
+    at_flags: u32,
+    user_space: &CurrentUserSpace,
+) -> Result<XattrFileCtx<'static>> {
+    let at_flags = XattrAtFlags::from_bits(at_flags)
+        .ok_or(Error::with_message(Errno::EINVAL, "invalid at flags"))?;
+
+    // Like Linux, a null path is accepted as an empty path if `AT_EMPTY_PATH` is specified.
+    let path = if path_ptr == 0 && at_flags.contains(XattrAtFlags::AT_EMPTY_PATH) {
+        CString::default()
+    } else {
+        user_space.read_cstring(path_ptr, MAX_FILENAME_LEN)?
+    };
+
+    Ok(XattrFileCtx::PathAt(dirfd, path, at_flags))
+}
+
+pub(super) fn read_xattr_args_from_user(
+    args_ptr: Vaddr,
+    args_size: usize,
+    user_space: &CurrentUserSpace,
+) -> Result<XattrArgs> {
+    if args_size < size_of::<XattrArgs>() {
+        return_errno_with_message!(Errno::EINVAL, "the xattr args size is too small");
+    }
+    if args_size > PAGE_SIZE {
+        return_errno_with_message!(Errno::E2BIG, "the xattr args size is too large");
+    }
+
+    let args = user_space.read_val::<XattrArgs>(args_ptr)?;
+
+    // The unknown trailing fields from newer user programs must be zeros.
+    let mut trailing_reader = user_space.reader(
+        args_ptr + size_of::<XattrArgs>(),
+        args_size - size_of::<XattrArgs>(),
+    )?;
+    while trailing_reader.has_remain() {
+        if trailing_reader.read_val::<u8>()? != 0 {
+            return_errno_with_message!(Errno::E2BIG, "the unknown xattr args are not zeros");
+        }
+    }
+
+    Ok(args)
+}
+
 pub(super) fn lookup_dentry_for_xattr<'a>(
     file_ctx: &'a XattrFileCtx<'a>,
     ctx: &'a Context,
 ) -> Result<Cow<'a, Dentry>> {
-    let lookup_dentry_from_fs =
-        |path: &CString, ctx: &Context, symlink_no_follow: bool| -> Result<Cow<'_, Dentry>> {
-            let path = path.to_string_lossy();
-            let fs_path = FsPath::new(AT_FDCWD, path.as_ref())?;
-            let fs = ctx.posix_thread.fs().resolver().read();
-            let dentry = if symlink_no_follow {
-                fs.lookup_no_follow(&fs_path)?
-            } else {
-                fs.lookup(&fs_path)?
-            };
-            Ok(Cow::Owned(dentry))
+    let lookup_dentry_from_fs = |dirfd: FileDesc,
+                                 path: &CString,
+                                 ctx: &Context,
+                                 symlink_no_follow: bool,
+                                 allow_empty_path: bool|
+     -> Result<Cow<'_, Dentry>> {
+        let path = path.to_string_lossy();
+        if path.is_empty() && !allow_empty_path {
+            return_errno_with_message!(Errno::ENOENT, "path is empty");
+        }
+        let fs_path = FsPath::new(dirfd, path.as_ref())?;
+        let fs = ctx.posix_thread.fs().resolver().read();
+        let dentry = if symlink_no_follow {
+            fs.lookup_no_follow(&fs_path)?
+        } else {
+            fs.lookup(&fs_path)?
         };
+        Ok(Cow::Owned(dentry))
+    };
 
     match file_ctx {
-        XattrFileCtx::Path(path) => lookup_dentry_from_fs(path, ctx, false),
-        XattrFileCtx::PathNoFollow(path) => lookup_dentry_from_fs(path, ctx, true),
+        XattrFileCtx::Path(path) => lookup_dentry_from_fs(AT_FDCWD, path, ctx, false, false),
+        XattrFileCtx::PathNoFollow(path) => lookup_dentry_from_fs(AT_FDCWD, path, ctx, true, false),
+        XattrFileCtx::PathAt(dirfd, path, at_flags) => lookup_dentry_from_fs(
+            *dirfd,
+            path,
+            ctx,
+            at_flags.contains(XattrAtFlags::AT_SYMLINK_NOFOLLOW),
+            at_flags.contains(XattrAtFlags::AT_EMPTY_PATH),
+        ),
         XattrFileCtx::FileHandle(file) => {
             let dentry = file.as_inode_or_err()?.dentry();
             Ok(Cow::Borrowed(dentry))
@@ -173,26 +279,96 @@ pub(super) fn parse_xattr_name(name_str: &str) -> Result<XattrName> {
     let xattr_name = XattrName::try_from_full_name(name_str.as_ref()).ok_or(
         Error::with_message(Errno::EOPNOTSUPP, "invalid xattr namespace"),
     )?;
+    if xattr_name.full_name_len() == xattr_name.namespace().prefix().len() {
+        return_errno_with_message!(Errno::EINVAL, "xattr name has only the namespace prefix");
+    }
     Ok(xattr_name)
 }
 
-pub(super) fn check_xattr_namespace(namespace: XattrNamespace, ctx: &Context) -> Result<()> {
-    let credentials = ctx.posix_thread.credentials();
-    let permitted_capset = credentials.permitted_capset();
-    let effective_capset = credentials.effective_capset();
+/// The kind of access to an xattr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum XattrAccess {
+    Read,
+    Write,
+}
 
-    match namespace {
+/// Checks whether the current thread can access the xattr of the file.
+///
+/// The rules are the same as Linux:
+///  - trusted xattrs are only accessible with `CAP_SYS_ADMIN`;
+///  - user xattrs are only supported on regular files and directories, and
+///    user xattrs of a sticky directory can only be written by the owner;
+///  - security xattrs can be read by everyone, but writing them requires
+///    `CAP_SYS_ADMIN` (or `CAP_SETFCAP` for file capabilities);
+///  - system xattrs are reserved for POSIX ACLs, which are not supported yet.
+///
+/// The permission bits of the file are checked by the underlying file system.
+pub(super) fn check_xattr_permission(
+    xattr_name: &XattrName,
+    dentry: &Dentry,
+    access: XattrAccess,
+    ctx: &Context,
+) -> Result<()> {
+    // For historical reasons, reading an inaccessible xattr fails with `ENODATA`.
+    let denied_errno = match access {
+        XattrAccess::Read => Errno::ENODATA,
+        XattrAccess::Write => Errno::EPERM,
+    };
+
+    match xattr_name.namespace() {
         XattrNamespace::Trusted => {
-            if !permitted_capset.contains(CapSet::SYS_ADMIN)
-                || !effective_capset.contains(CapSet::SYS_ADMIN)
+            if !is_capable(CapSet::SYS_ADMIN, ctx) {
+                return_errno_with_message!(
+                    denied_errno,
+                    "try to access trusted xattr without CAP_SYS_ADMIN"
+                );
+            }
+        }
+        XattrNamespace::User => {
+            let type_ = dentry.type_();
+            if type_ != InodeType::File && type_ != InodeType::Dir {
+                return_errno_with_message!(
+                    denied_errno,
+                    "user xattr is not supported on the file type"
+                );
+            }
+
+            if access == XattrAccess::Write
+                && type_ == InodeType::Dir
+                && dentry.mode()?.has_sticky_bit()
+                && dentry.owner()? != ctx.posix_thread.credentials().fsuid()
+                && !is_capable(CapSet::FOWNER, ctx)
             {
                 return_errno_with_message!(
                     Errno::EPERM,
-                    "try to access trusted xattr without CAP_SYS_ADMIN"
+                    "only the owner can write user xattr of a sticky directory"
                 );
             }
         }
-        _ => {}
+        XattrNamespace::Security => {
+            let required_cap = if xattr_name.full_name() == XATTR_NAME_CAPS {
+                CapSet::SETFCAP
+            } else {
+                CapSet::SYS_ADMIN
+            };
+            if access == XattrAccess::Write && !is_capable(required_cap, ctx) {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "try to write security xattr without the capability"
+                );
+            }
+        }
+        XattrNamespace::System => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "POSIX ACLs are not supported");
+        }
     }
     Ok(())
 }
+
+/// The xattr name of file capabilities.
+const XATTR_NAME_CAPS: &str = "security.capability";
+
+pub(super) fn is_capable(cap: CapSet, ctx: &Context) -> bool {
+    let credentials = ctx.posix_thread.credentials();
+    credentials.permitted_capset().contains(cap) && credentials.effective_capset().contains(cap)
+}
@@ -22,7 +22,10 @@ use crate::{
     process::{
         posix_thread::AsPosixThread,
         signal::{
-            constants::{SIGKILL, SIGSTOP},
+            constants::{
+                SIGBUS, SIGFPE, SIGILL, SIGKILL, SIGSEGV, SIGSTOP, SI_QUEUE, SI_TIMER, SI_TKILL,
+                SI_USER,
+            },
             sig_mask::{AtomicSigMask, SigMask},
             signals::Signal,
             PollHandle, Pollable, Pollee, SigEvents, SigEventsFilter,
@@ -346,23 +349,47 @@ trait ToSignalfdSiginfo {
 impl ToSignalfdSiginfo for Box<dyn Signal> {
     fn to_signalfd_siginfo(&self) -> SignalfdSiginfo {
         let siginfo = self.to_info();
+
+        // The union fields are only meaningful for certain kinds of signals.
+        let (ssi_pid, ssi_uid) = if matches!(siginfo.si_code, SI_USER | SI_TKILL | SI_QUEUE) {
+            (siginfo.si_pid(), u32::from(siginfo.si_uid()))
+        } else {
+            (0, 0)
+        };
+        let (ssi_tid, ssi_overrun, ssi_int, ssi_ptr) = if siginfo.si_code == SI_TIMER {
+            let value = siginfo.si_value();
+            (
+                siginfo.si_timerid() as u32,
+                siginfo.si_overrun() as u32,
+                value.read_int(),
+                value.read_ptr() as u64,
+            )
+        } else {
+            (0, 0, 0, 0)
+        };
+        let ssi_addr = if matches!(self.num(), SIGILL | SIGFPE | SIGSEGV | SIGBUS) {
+            siginfo.si_addr() as u64
+        } else {
+            0
+        };
+
         SignalfdSiginfo {
             ssi_signo: siginfo.si_signo as _,
             ssi_errno: siginfo.si_errno,
             ssi_code: siginfo.si_code,
-            ssi_pid: 0,
-            ssi_uid: 0,
+            ssi_pid,
+            ssi_uid,
             ssi_fd: 0,
-            ssi_tid: 0,
+            ssi_tid,
             ssi_band: 0,
-            ssi_overrun: 0,
+            ssi_overrun,
             ssi_trapno: 0,
             ssi_status: 0,
-            ssi_int: 0,
-            ssi_ptr: 0,
+            ssi_int,
+            ssi_ptr,
             ssi_utime: 0,
             ssi_stime: 0,
-            ssi_addr: 0,
+            ssi_addr,
             _pad: [0; 48],
         }
     }
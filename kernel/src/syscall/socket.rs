@@ -5,7 +5,12 @@ use crate::{
     fs::{file_handle::FileLike, file_table::FdFlags},
     net::socket::{
         ip::{datagram::DatagramSocket, stream::StreamSocket},
-        netlink::{is_valid_protocol, NetlinkRouteSocket, StandardNetlinkProtocol},
+        netlink::{
+            is_valid_protocol, NetlinkConnectorSocket, NetlinkGenericSocket,
+            NetlinkNetfilterSocket, NetlinkRouteSocket, NetlinkUeventSocket,
+            StandardNetlinkProtocol,
+        },
+        packet::PacketSocket,
         unix::UnixStreamSocket,
         vsock::VsockStreamSocket,
     },
@@ -23,26 +28,46 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
     );
     let is_nonblocking = sock_flags.contains(SockFlags::SOCK_NONBLOCK);
     let file_like = match (domain, sock_type) {
-        // FIXME: SOCK_SEQPACKET is added to run fcntl_test, not supported yet.
-        (CSocketAddrFamily::AF_UNIX, SockType::SOCK_STREAM | SockType::SOCK_SEQPACKET) => {
-            UnixStreamSocket::new(is_nonblocking) as Arc<dyn FileLike>
+        (CSocketAddrFamily::AF_UNIX, SockType::SOCK_STREAM) => {
+            UnixStreamSocket::new(is_nonblocking, false) as Arc<dyn FileLike>
         }
-        (CSocketAddrFamily::AF_INET, SockType::SOCK_STREAM) => {
+        (CSocketAddrFamily::AF_UNIX, SockType::SOCK_SEQPACKET) => {
+            UnixStreamSocket::new(is_nonblocking, true) as Arc<dyn FileLike>
+        }
+        (
+            family @ (CSocketAddrFamily::AF_INET | CSocketAddrFamily::AF_INET6),
+            SockType::SOCK_STREAM,
+        ) => {
             let protocol = Protocol::try_from(protocol)?;
             debug!("protocol = {:?}", protocol);
+            let is_ipv6 = family == CSocketAddrFamily::AF_INET6;
             match protocol {
                 Protocol::IPPROTO_IP | Protocol::IPPROTO_TCP => {
-                    StreamSocket::new(is_nonblocking) as Arc<dyn FileLike>
+                    StreamSocket::new(is_nonblocking, is_ipv6) as Arc<dyn FileLike>
                 }
                 _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported protocol"),
             }
         }
-        (CSocketAddrFamily::AF_INET, SockType::SOCK_DGRAM) => {
+        (
+            family @ (CSocketAddrFamily::AF_INET | CSocketAddrFamily::AF_INET6),
+            SockType::SOCK_DGRAM,
+        ) => {
             let protocol = Protocol::try_from(protocol)?;
             debug!("protocol = {:?}", protocol);
+            let is_ipv6 = family == CSocketAddrFamily::AF_INET6;
             match protocol {
                 Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP => {
-                    DatagramSocket::new(is_nonblocking) as Arc<dyn FileLike>
+                    DatagramSocket::new(is_nonblocking, is_ipv6) as Arc<dyn FileLike>
+                }
+                Protocol::IPPROTO_ICMP if !is_ipv6 => {
+                    let credentials = ctx.posix_thread.credentials();
+                    DatagramSocket::new_ping(is_nonblocking, false, &credentials)?
+                        as Arc<dyn FileLike>
+                }
+                Protocol::IPPROTO_ICMPV6 if is_ipv6 => {
+                    let credentials = ctx.posix_thread.credentials();
+                    DatagramSocket::new_ping(is_nonblocking, true, &credentials)?
+                        as Arc<dyn FileLike>
                 }
                 _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported protocol"),
             }
@@ -54,6 +79,18 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
                 Ok(StandardNetlinkProtocol::ROUTE) => {
                     Arc::new(NetlinkRouteSocket::new(is_nonblocking))
                 }
+                Ok(StandardNetlinkProtocol::CONNECTOR) => {
+                    Arc::new(NetlinkConnectorSocket::new(is_nonblocking))
+                }
+                Ok(StandardNetlinkProtocol::KOBJECT_UEVENT) => {
+                    Arc::new(NetlinkUeventSocket::new(is_nonblocking))
+                }
+                Ok(StandardNetlinkProtocol::NETFILTER) => {
+                    Arc::new(NetlinkNetfilterSocket::new(is_nonblocking))
+                }
+                Ok(StandardNetlinkProtocol::GENERIC) => {
+                    Arc::new(NetlinkGenericSocket::new(is_nonblocking))
+                }
                 Ok(_) => {
                     return_errno_with_message!(
                         Errno::EAFNOSUPPORT,
@@ -71,6 +108,16 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
                 }
             }
         }
+        (CSocketAddrFamily::AF_PACKET, sock_type @ (SockType::SOCK_RAW | SockType::SOCK_DGRAM)) => {
+            // The protocol is in the network byte order.
+            let protocol = u16::from_be(protocol as u16);
+            debug!("protocol = {:#x}", protocol);
+            let is_raw = matches!(sock_type, SockType::SOCK_RAW);
+            PacketSocket::new(is_raw, protocol, is_nonblocking)? as Arc<dyn FileLike>
+        }
+        (CSocketAddrFamily::AF_PACKET, _) => {
+            return_errno_with_message!(Errno::ESOCKTNOSUPPORT, "unsupported packet socket type")
+        }
         (CSocketAddrFamily::AF_VSOCK, SockType::SOCK_STREAM) => {
             Arc::new(VsockStreamSocket::new(is_nonblocking)) as Arc<dyn FileLike>
         }
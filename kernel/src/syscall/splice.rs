@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The pipe-centric data movement system calls: `splice`, `tee`, and `vmsplice`.
+//!
+//! The data is moved between the pipe buffer and the other file directly, so
+//! there is only one copy instead of two copies via a user buffer.
+
+use super::SyscallReturn;
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        file_table::{FileDesc, WithFileTable},
+        pipe::{PipeReader, PipeWriter},
+    },
+    prelude::*,
+    process::signal::Pollable,
+    util::{VmReaderArray, VmWriterArray},
+};
+
+pub fn sys_splice(
+    fd_in: FileDesc,
+    off_in_ptr: Vaddr,
+    fd_out: FileDesc,
+    off_out_ptr: Vaddr,
+    len: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SpliceFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid splice flags"))?;
+    debug!(
+        "fd_in = {}, off_in_ptr = 0x{:x}, fd_out = {}, off_out_ptr = 0x{:x}, len = 0x{:x}, flags = {:?}",
+        fd_in, off_in_ptr, fd_out, off_out_ptr, len, flags
+    );
+
+    let (file_in, file_out) = get_files(fd_in, fd_out, ctx)?;
+    if !file_in.access_mode().is_readable() {
+        return_errno_with_message!(Errno::EBADF, "the input file is not readable");
+    }
+    if !file_out.access_mode().is_writable() {
+        return_errno_with_message!(Errno::EBADF, "the output file is not writable");
+    }
+
+    let is_nonblocking = flags.contains(SpliceFlags::NONBLOCK);
+    let len = len.min(MAX_RW_COUNT);
+
+    let spliced_len = match (
+        file_in.downcast_ref::<PipeReader>(),
+        file_out.downcast_ref::<PipeWriter>(),
+    ) {
+        (Some(pipe_in), Some(pipe_out)) => {
+            if off_in_ptr != 0 || off_out_ptr != 0 {
+                return_errno_with_message!(Errno::ESPIPE, "pipes do not have offsets");
+            }
+            splice_pipe_to_pipe(pipe_in, pipe_out, len, is_nonblocking, false)?
+        }
+        (Some(pipe_in), None) => {
+            if off_in_ptr != 0 {
+                return_errno_with_message!(Errno::ESPIPE, "pipes do not have offsets");
+            }
+            let mut offset = read_offset(off_out_ptr, ctx)?;
+            let spliced_len =
+                splice_pipe_to_file(pipe_in, &*file_out, offset.as_mut(), len, is_nonblocking)?;
+            write_offset(off_out_ptr, offset, ctx)?;
+            spliced_len
+        }
+        (None, Some(pipe_out)) => {
+            if off_out_ptr != 0 {
+                return_errno_with_message!(Errno::ESPIPE, "pipes do not have offsets");
+            }
+            let mut offset = read_offset(off_in_ptr, ctx)?;
+            let spliced_len =
+                splice_file_to_pipe(&*file_in, offset.as_mut(), pipe_out, len, is_nonblocking)?;
+            write_offset(off_in_ptr, offset, ctx)?;
+            spliced_len
+        }
+        (None, None) => {
+            return_errno_with_message!(Errno::EINVAL, "neither of the files is a pipe");
+        }
+    };
+
+    Ok(SyscallReturn::Return(spliced_len as _))
+}
+
+pub fn sys_tee(
+    fd_in: FileDesc,
+    fd_out: FileDesc,
+    len: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SpliceFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid tee flags"))?;
+    debug!(
+        "fd_in = {}, fd_out = {}, len = 0x{:x}, flags = {:?}",
+        fd_in, fd_out, len, flags
+    );
+
+    let (file_in, file_out) = get_files(fd_in, fd_out, ctx)?;
+    let (Some(pipe_in), Some(pipe_out)) = (
+        file_in.downcast_ref::<PipeReader>(),
+        file_out.downcast_ref::<PipeWriter>(),
+    ) else {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "tee requires the read end and the write end of pipes"
+        );
+    };
+
+    let is_nonblocking = flags.contains(SpliceFlags::NONBLOCK);
+    let len = len.min(MAX_RW_COUNT);
+    let teed_len = splice_pipe_to_pipe(pipe_in, pipe_out, len, is_nonblocking, true)?;
+
+    Ok(SyscallReturn::Return(teed_len as _))
+}
+
+pub fn sys_vmsplice(
+    fd: FileDesc,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SpliceFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid vmsplice flags"))?;
+    debug!(
+        "fd = {}, io_vec_ptr = 0x{:x}, io_vec_count = {}, flags = {:?}",
+        fd, io_vec_ptr, io_vec_count, flags
+    );
+
+    if io_vec_count > IOV_MAX {
+        return_errno_with_message!(Errno::EINVAL, "too many IO vectors");
+    }
+
+    let file = ctx
+        .thread_local
+        .borrow_file_table_mut()
+        .read_with(|inner| inner.get_file(fd).cloned())?;
+
+    let is_nonblocking = flags.contains(SpliceFlags::NONBLOCK);
+    let user_space = ctx.user_space();
+    // TODO: Map the user pages to the pipe instead of copying them if `SPLICE_F_GIFT` is
+    // specified.
+    let len = if let Some(pipe_out) = file.downcast_ref::<PipeWriter>() {
+        let mut reader_array =
+            VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+        pipe_out.write_from(&mut reader_array, is_nonblocking)?
+    } else if let Some(pipe_in) = file.downcast_ref::<PipeReader>() {
+        let mut writer_array =
+            VmWriterArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+        pipe_in.read_to(&mut writer_array, is_nonblocking)?
+    } else {
+        return_errno_with_message!(Errno::EBADF, "the file is not a pipe");
+    };
+
+    Ok(SyscallReturn::Return(len as _))
+}
+
+/// Moves at most `len` bytes from `file` to `pipe_out`.
+///
+/// If `offset` is `Some(_)`, the file is read from the offset, and the offset is advanced.
+/// Otherwise, the file is read from (and advances) its file offset.
+pub(super) fn splice_file_to_pipe(
+    file: &dyn FileLike,
+    mut offset: Option<&mut usize>,
+    pipe_out: &PipeWriter,
+    len: usize,
+    is_nonblocking: bool,
+) -> Result<usize> {
+    pipe_out.write_with(len, is_nonblocking, &mut |writer| {
+        if let Some(offset) = offset.as_deref_mut() {
+            let read_len = file.read_at(*offset, writer)?;
+            *offset += read_len;
+            Ok(read_len)
+        } else {
+            file.read(writer)
+        }
+    })
+}
+
+/// Moves at most `len` bytes from `pipe_in` to `file`.
+///
+/// If `offset` is `Some(_)`, the file is written at the offset, and the offset is advanced.
+/// Otherwise, the file is written at (and advances) its file offset.
+fn splice_pipe_to_file(
+    pipe_in: &PipeReader,
+    file: &dyn FileLike,
+    mut offset: Option<&mut usize>,
+    len: usize,
+    is_nonblocking: bool,
+) -> Result<usize> {
+    pipe_in.read_with(len, is_nonblocking, &mut |reader| {
+        if let Some(offset) = offset.as_deref_mut() {
+            let written_len = file.write_at(*offset, reader)?;
+            *offset += written_len;
+            Ok(written_len)
+        } else {
+            file.write(reader)
+        }
+    })
+}
+
+/// Moves (or copies if `is_tee` is true) at most `len` bytes from `pipe_in` to `pipe_out`.
+fn splice_pipe_to_pipe(
+    pipe_in: &PipeReader,
+    pipe_out: &PipeWriter,
+    len: usize,
+    is_nonblocking: bool,
+    is_tee: bool,
+) -> Result<usize> {
+    loop {
+        // The input pipe may block, but the output pipe must not, since the input pipe is locked
+        // during the transfer. If the output pipe is full, we wait for it below instead.
+        let mut is_out_full = false;
+        let mut write_fn = |reader: &mut VmReader| match pipe_out.write_from(reader, true) {
+            Err(err) if err.error() == Errno::EAGAIN => {
+                is_out_full = true;
+                Ok(0)
+            }
+            res => res,
+        };
+        let res = if is_tee {
+            pipe_in.peek_with(len, is_nonblocking, &mut write_fn)
+        } else {
+            pipe_in.read_with(len, is_nonblocking, &mut write_fn)
+        };
+
+        if !matches!(res, Ok(0)) || !is_out_full {
+            return res;
+        }
+        if is_nonblocking {
+            return_errno_with_message!(Errno::EAGAIN, "the output pipe is full");
+        }
+        pipe_out.wait_events(IoEvents::OUT, None, || {
+            if pipe_out.poll(IoEvents::OUT, None).is_empty() {
+                return_errno_with_message!(Errno::EAGAIN, "the output pipe is full");
+            }
+            Ok(())
+        })?;
+    }
+}
+
+pub(super) fn get_files(
+    fd_in: FileDesc,
+    fd_out: FileDesc,
+    ctx: &Context,
+) -> Result<(Arc<dyn FileLike>, Arc<dyn FileLike>)> {
+    ctx.thread_local.borrow_file_table_mut().read_with(|inner| {
+        let file_in = inner.get_file(fd_in)?.clone();
+        let file_out = inner.get_file(fd_out)?.clone();
+        Ok((file_in, file_out))
+    })
+}
+
+pub(super) fn read_offset(offset_ptr: Vaddr, ctx: &Context) -> Result<Option<usize>> {
+    if offset_ptr == 0 {
+        return Ok(None);
+    }
+
+    let offset: i64 = ctx.user_space().read_val(offset_ptr)?;
+    if offset < 0 {
+        return_errno_with_message!(Errno::EINVAL, "offset cannot be negative");
+    }
+    Ok(Some(offset as usize))
+}
+
+pub(super) fn write_offset(offset_ptr: Vaddr, offset: Option<usize>, ctx: &Context) -> Result<()> {
+    if let Some(offset) = offset {
+        ctx.user_space().write_val(offset_ptr, &(offset as i64))?;
+    }
+    Ok(())
+}
+
+/// The maximum number of bytes moved by a call, which is the same as Linux's `MAX_RW_COUNT`.
+pub(super) const MAX_RW_COUNT: usize = i32::MAX as usize & !(PAGE_SIZE - 1);
+
+/// The maximum number of IO vectors, which is the same as Linux's `UIO_MAXIOV`.
+const IOV_MAX: usize = 1024;
+
+bitflags! {
+    struct SpliceFlags: u32 {
+        /// Moves pages instead of copying (only a hint).
+        const MOVE = 0x01;
+        /// Does not block on I/O.
+        const NONBLOCK = 0x02;
+        /// Expects more data.
+        const MORE = 0x04;
+        /// Gifts the user pages to the kernel (only for `vmsplice`).
+        const GIFT = 0x08;
+    }
+}
@@ -5,7 +5,8 @@ use crate::{
     fs::{
         file_table::{get_file_fast, FileDesc},
         fs_resolver::FsPath,
-        utils::{SuperBlock, PATH_MAX},
+        path::Dentry,
+        utils::PATH_MAX,
     },
     prelude::*,
 };
@@ -20,7 +21,7 @@ pub fn sys_statfs(path_ptr: Vaddr, statfs_buf_ptr: Vaddr, ctx: &Context) -> Resu
         let fs_path = FsPath::try_from(path.as_ref())?;
         ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
     };
-    let statfs = Statfs::from(dentry.fs().sb());
+    let statfs = Statfs::new(&dentry);
     user_space.write_val(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -28,13 +29,11 @@ pub fn sys_statfs(path_ptr: Vaddr, statfs_buf_ptr: Vaddr, ctx: &Context) -> Resu
 pub fn sys_fstatfs(fd: FileDesc, statfs_buf_ptr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
     debug!("fd = {}, statfs_buf_addr = 0x{:x}", fd, statfs_buf_ptr);
 
-    let fs = {
+    let statfs = {
         let mut file_table = ctx.thread_local.borrow_file_table_mut();
         let file = get_file_fast!(&mut file_table, fd);
-        file.as_inode_or_err()?.dentry().fs()
+        Statfs::new(file.as_inode_or_err()?.dentry())
     };
-
-    let statfs = Statfs::from(fs.sb());
     ctx.user_space().write_val(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -69,8 +68,14 @@ struct Statfs {
     f_spare: [u64; 4],
 }
 
-impl From<SuperBlock> for Statfs {
-    fn from(sb: SuperBlock) -> Self {
+/// The flag indicating that `f_flags` is supported.
+const ST_VALID: u64 = 0x0020;
+
+impl Statfs {
+    fn new(dentry: &Dentry) -> Self {
+        let sb = dentry.fs().sb();
+        let mount_flags = dentry.mount_node().flags();
+
         Self {
             f_type: sb.magic,
             f_bsize: sb.bsize,
@@ -82,7 +87,7 @@ impl From<SuperBlock> for Statfs {
             f_fsid: sb.fsid,
             f_namelen: sb.namelen,
             f_frsize: sb.frsize,
-            f_flags: sb.flags,
+            f_flags: ST_VALID | mount_flags.bits() as u64 | sb.flags,
             f_spare: [0u64; 4],
         }
     }
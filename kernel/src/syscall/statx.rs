@@ -4,7 +4,13 @@ use core::time::Duration;
 
 use super::SyscallReturn;
 use crate::{
-    fs::{device::DeviceId, file_table::FileDesc, fs_resolver::FsPath, utils::Metadata},
+    fs::{
+        device::DeviceId,
+        file_table::{get_file_fast, FileDesc},
+        fs_resolver::FsPath,
+        path::Dentry,
+        utils::{ExtendedMetadata, FileAttributes, Metadata},
+    },
     prelude::*,
     syscall::constants::MAX_FILENAME_LEN,
 };
@@ -44,19 +50,29 @@ pub fn sys_statx(
         );
     }
 
-    let dentry = {
-        let filename = filename.to_string_lossy();
-        let fs_path = FsPath::new(dirfd, filename.as_ref())?;
-        let fs = ctx.posix_thread.fs().resolver().read();
-        if flags.contains(StatxFlags::AT_SYMLINK_NOFOLLOW) {
-            fs.lookup_no_follow(&fs_path)?
-        } else {
-            fs.lookup(&fs_path)?
+    let statx = if filename.is_empty() && dirfd >= 0 {
+        let mut file_table = ctx.thread_local.borrow_file_table_mut();
+        let file = get_file_fast!(&mut file_table, dirfd);
+        match file.as_inode_or_err() {
+            Ok(inode_handle) => Statx::from_dentry(inode_handle.dentry(), mask),
+            // Files that are not related to an inode (e.g., pipes and sockets)
+            // only have the basic metadata.
+            Err(_) => Statx::new(file.metadata(), ExtendedMetadata::default(), None, mask),
         }
+    } else {
+        let dentry = {
+            let filename = filename.to_string_lossy();
+            let fs_path = FsPath::new(dirfd, filename.as_ref())?;
+            let fs = ctx.posix_thread.fs().resolver().read();
+            if flags.contains(StatxFlags::AT_SYMLINK_NOFOLLOW) {
+                fs.lookup_no_follow(&fs_path)?
+            } else {
+                fs.lookup(&fs_path)?
+            }
+        };
+        Statx::from_dentry(&dentry, mask)
     };
 
-    let statx = Statx::from(dentry.metadata());
-
     user_space.write_val(statx_buf_ptr, &statx)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -114,15 +130,50 @@ pub struct Statx {
     __spare3: [u64; 12],
 }
 
-impl From<Metadata> for Statx {
-    fn from(info: Metadata) -> Self {
+impl Statx {
+    fn from_dentry(dentry: &Dentry, mask: StatxMask) -> Self {
+        let mut extended = dentry.inode().extended_metadata();
+        extended.attributes_mask |= FileAttributes::MOUNT_ROOT;
+        if dentry.is_root_of_mount() {
+            extended.attributes |= FileAttributes::MOUNT_ROOT;
+        }
+
+        Self::new(
+            dentry.metadata(),
+            extended,
+            Some(dentry.mount_node().id()),
+            mask,
+        )
+    }
+
+    /// Creates a `Statx` from the metadata.
+    ///
+    /// Like Linux, the basic stats are always filled, whereas the birth time
+    /// is only filled if it is requested in `mask` and known.
+    fn new(
+        info: Metadata,
+        extended: ExtendedMetadata,
+        mnt_id: Option<usize>,
+        mask: StatxMask,
+    ) -> Self {
         let devid = DeviceId::from(info.dev);
         let rdevid = DeviceId::from(info.rdev);
+
+        let mut stx_mask = StatxMask::STATX_BASIC_STATS;
+        let btime = extended
+            .btime
+            .filter(|_| mask.contains(StatxMask::STATX_BTIME));
+        if btime.is_some() {
+            stx_mask |= StatxMask::STATX_BTIME;
+        }
+        if mnt_id.is_some() {
+            stx_mask |= StatxMask::STATX_MNT_ID;
+        }
+
         Self {
-            // FIXME: All zero fields below are dummy implementations that need to be improved in the future.
-            stx_mask: 0,
+            stx_mask: stx_mask.bits(),
             stx_blksize: info.blk_size as u32,
-            stx_attributes: 0,
+            stx_attributes: extended.attributes.bits(),
             stx_nlink: info.nlinks as u32,
             stx_uid: info.uid.into(),
             stx_gid: info.gid.into(),
@@ -131,16 +182,17 @@ impl From<Metadata> for Statx {
             stx_ino: info.ino,
             stx_size: info.size as u64,
             stx_blocks: (info.blocks * (info.blk_size / 512)) as u64,
-            stx_attributes_mask: 0,
+            stx_attributes_mask: extended.attributes_mask.bits(),
             stx_atime: StatxTimestamp::from(info.atime),
-            stx_btime: StatxTimestamp::from(info.atime),
+            stx_btime: btime.map(StatxTimestamp::from).unwrap_or_default(),
             stx_ctime: StatxTimestamp::from(info.ctime),
-            stx_mtime: StatxTimestamp::from(info.ctime),
+            stx_mtime: StatxTimestamp::from(info.mtime),
             stx_rdev_major: rdevid.major(),
             stx_rdev_minor: rdevid.minor(),
             stx_dev_major: devid.major(),
             stx_dev_minor: devid.minor(),
-            stx_mnt_id: 0,
+            stx_mnt_id: mnt_id.unwrap_or(0) as u64,
+            // FIXME: Direct I/O is not supported, so the alignments are left zero.
             stx_dio_mem_align: 0,
             stx_dio_offset_align: 0,
             __spare3: [0; 12],
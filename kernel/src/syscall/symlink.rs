@@ -5,7 +5,6 @@ use crate::{
     fs::{
         file_table::FileDesc,
         fs_resolver::{FsPath, AT_FDCWD},
-        utils::{InodeMode, InodeType},
     },
     prelude::*,
     syscall::constants::MAX_FILENAME_LEN,
@@ -42,12 +41,7 @@ pub fn sys_symlinkat(
             .lookup_dir_and_new_basename(&fs_path, false)?
     };
 
-    let new_dentry = dir_dentry.new_fs_child(
-        &link_name,
-        InodeType::SymLink,
-        InodeMode::from_bits_truncate(0o777),
-    )?;
-    new_dentry.inode().write_link(&target)?;
+    let _ = dir_dentry.new_fs_symlink(&link_name, &target)?;
     Ok(SyscallReturn::Return(0))
 }
 
@@ -3,7 +3,7 @@
 use aster_time::read_monotonic_time;
 
 use super::SyscallReturn;
-use crate::prelude::*;
+use crate::{fs::utils::nr_cache_pages, prelude::*, process::process_table, sched::loadavg};
 
 #[derive(Debug, Default, Clone, Copy, Pod)]
 #[repr(C)]
@@ -22,12 +22,28 @@ pub struct sysinfo {
     mem_unit: u32,   /* Memory unit size in bytes */
 }
 
+/// The number of fractional bits of the load averages in `sysinfo`.
+const SI_LOAD_SHIFT: u32 = 16;
+
 pub fn sys_sysinfo(sysinfo_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let loads = loadavg::get_loadavg()
+        .map(|load| (load.to_bits() as u64) << (SI_LOAD_SHIFT - loadavg::FixedPoint::FRAC_NBITS));
+
+    // Like Linux, `procs` is the number of threads, not processes.
+    let nr_threads = process_table::process_table_mut()
+        .iter()
+        .map(|process| process.tasks().lock().as_slice().len())
+        .sum::<usize>();
+
     let info = sysinfo {
         uptime: read_monotonic_time().as_secs() as i64,
+        loads,
         totalram: crate::vm::mem_total() as u64,
         freeram: osdk_frame_allocator::load_total_free_size() as u64,
-        ..Default::default() // TODO: add other system information
+        bufferram: (nr_cache_pages() * PAGE_SIZE) as u64,
+        procs: nr_threads.min(u16::MAX as usize) as u16,
+        mem_unit: 1,
+        ..Default::default()
     };
     ctx.user_space().write_val(sysinfo_addr, &info)?;
     Ok(SyscallReturn::Return(0))
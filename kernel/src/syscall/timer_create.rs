@@ -10,14 +10,18 @@ use crate::{
         posix_thread::{thread_table, AsPosixThread},
         process_table,
         signal::{
-            c_types::{sigevent_t, SigNotify},
+            c_types::{sigevent_t, sigval_t, SigNotify},
             constants::SIGALRM,
             sig_num::SigNum,
-            signals::kernel::KernelSignal,
+            signals::timer::{TimerOverrun, TimerSignal},
         },
+        PosixTimer, Process,
     },
     syscall::ClockId,
-    thread::work_queue::{submit_work_item, work_item::WorkItem},
+    thread::{
+        work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
+        Thread,
+    },
     time::{
         clockid_t,
         clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
@@ -38,89 +42,136 @@ pub fn sys_timer_create(
         );
     }
 
-    let current_process = current!();
-    let sent_signal: Box<dyn Fn() + Send + Sync + 'static> = {
-        // If `sigevent_addr` is NULL, use the default method (like `sys_alarm`) to send signal.
-        if sigevent_addr == 0 {
-            let process = current_process.clone();
-            let signal = KernelSignal::new(SIGALRM);
-            Box::new(move || {
-                process.enqueue_signal(signal);
-            })
-        // Determine the timeout action through `sigevent`.
-        } else {
-            let sig_event = ctx.user_space().read_val::<sigevent_t>(sigevent_addr)?;
-            let sigev_notify = SigNotify::try_from(sig_event.sigev_notify)?;
-            let signo = sig_event.sigev_signo;
-            match sigev_notify {
-                // Do nothing when the timer is expired.
-                SigNotify::SIGEV_NONE => Box::new(|| {}),
-                // Send a signal to the current process when the timer is expired.
-                SigNotify::SIGEV_SIGNAL => {
-                    let process = current_process.clone();
-                    let signal = KernelSignal::new(SigNum::try_from(signo as u8)?);
-                    Box::new(move || {
-                        process.enqueue_signal(signal);
-                    })
-                }
-                // Spawn a posix thread to run the `sigev_function`, which is stored in
-                // `sig_event.sigev_un._sigev_thread`.
-                //
-                // TODO: enable this instructions. Currently the system does not provide an API to spawn
-                // a posix thread to run a specified function.
-                SigNotify::SIGEV_THREAD => {
-                    unimplemented!()
-                }
-                // Send a signal to the specified thread when the timer is expired.
-                SigNotify::SIGEV_THREAD_ID => {
-                    let tid = sig_event.sigev_un.read_tid() as u32;
-                    let thread = thread_table::get_thread(tid).ok_or_else(|| {
-                        Error::with_message(Errno::EINVAL, "target thread does not exist")
-                    })?;
-                    let posix_thread = thread.as_posix_thread().unwrap();
-                    if posix_thread.process().pid() != current_process.pid() {
-                        return_errno_with_message!(
-                            Errno::EINVAL,
-                            "target thread should belong to current process"
-                        );
-                    }
-                    let signal = KernelSignal::new(SigNum::try_from(signo as u8)?);
-                    Box::new(move || {
-                        if let Some(thread) = thread.as_posix_thread() {
-                            thread.enqueue_signal(Box::new(signal));
-                        }
-                    })
+    let notification = if sigevent_addr == 0 {
+        // If `sigevent_addr` is NULL, send `SIGALRM` to the current process with the timer ID as
+        // the signal value.
+        Some((
+            SignalTarget::Process(ctx.posix_thread.weak_process()),
+            SIGALRM,
+            None,
+        ))
+    } else {
+        let sig_event = ctx.user_space().read_val::<sigevent_t>(sigevent_addr)?;
+        let sigev_notify = SigNotify::try_from(sig_event.sigev_notify)?;
+        let signo = sig_event.sigev_signo;
+        debug!("sigev_notify = {:?}, signo = {}", sigev_notify, signo);
+
+        match sigev_notify {
+            // Do nothing when the timer is expired.
+            SigNotify::SIGEV_NONE => None,
+            // Send a signal to the current process when the timer is expired.
+            //
+            // `SIGEV_THREAD` is implemented by the C library, which receives the signal in a
+            // helper thread and then runs the function. So the kernel handles it in the same way
+            // as `SIGEV_SIGNAL`.
+            SigNotify::SIGEV_SIGNAL | SigNotify::SIGEV_THREAD => Some((
+                SignalTarget::Process(ctx.posix_thread.weak_process()),
+                parse_sig_num(signo)?,
+                Some(sig_event.sigev_value),
+            )),
+            // Send a signal to the specified thread when the timer is expired.
+            SigNotify::SIGEV_THREAD_ID => {
+                let tid = sig_event.sigev_un.read_tid() as u32;
+                let thread = thread_table::get_thread(tid).ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "target thread does not exist")
+                })?;
+                let posix_thread = thread.as_posix_thread().unwrap();
+                if posix_thread.process().pid() != ctx.process.pid() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "target thread should belong to current process"
+                    );
                 }
+                Some((
+                    SignalTarget::Thread(Arc::downgrade(&thread)),
+                    parse_sig_num(signo)?,
+                    Some(sig_event.sigev_value),
+                ))
             }
         }
     };
 
-    let work_func = sent_signal;
-    let work_item = WorkItem::new(work_func);
-    let func = move || {
-        submit_work_item(
-            work_item.clone(),
-            crate::thread::work_queue::WorkPriority::High,
-        );
-    };
+    let timer_id = ctx.process.timer_manager().add_posix_timer(|timer_id| {
+        let overrun = Arc::new(TimerOverrun::default());
+
+        let func: Box<dyn Fn() + Send + Sync> = match notification {
+            Some((target, sig_num, value)) => {
+                let value = value.unwrap_or_else(|| {
+                    let mut value = sigval_t::new_zeroed();
+                    value.set_int(timer_id as i32);
+                    value
+                });
+
+                let signal_overrun = overrun.clone();
+                let work_func = move || {
+                    let signal =
+                        TimerSignal::new(sig_num, timer_id as i32, value, signal_overrun.clone());
+                    target.enqueue_signal(signal);
+                };
+                let work_item = WorkItem::new(Box::new(work_func));
+
+                let overrun = overrun.clone();
+                Box::new(move || {
+                    // The expirations are counted here, since the work item will not run once
+                    // again if it is submitted when it is pending.
+                    if overrun.on_expire() {
+                        submit_work_item(work_item.clone(), WorkPriority::High);
+                    }
+                })
+            }
+            None => Box::new(|| {}),
+        };
 
-    let timer = create_timer(clockid, func, ctx)?;
+        let timer = create_timer(clockid, func, ctx)?;
+        Ok(PosixTimer::new(timer, overrun))
+    })?;
+    ctx.user_space()
+        .write_val(timer_id_addr, &(timer_id as i32))?;
 
-    let timer_id = current_process.timer_manager().add_posix_timer(timer);
-    ctx.user_space().write_val(timer_id_addr, &timer_id)?;
     Ok(SyscallReturn::Return(0))
 }
 
-pub fn sys_timer_delete(timer_id: usize, _ctx: &Context) -> Result<SyscallReturn> {
-    let current_process = current!();
-    let Some(timer) = current_process.timer_manager().remove_posix_timer(timer_id) else {
+pub fn sys_timer_delete(timer_id: usize, ctx: &Context) -> Result<SyscallReturn> {
+    let Some(posix_timer) = ctx.process.timer_manager().remove_posix_timer(timer_id) else {
         return_errno_with_message!(Errno::EINVAL, "invalid timer ID");
     };
 
-    timer.cancel();
+    posix_timer.timer().cancel();
     Ok(SyscallReturn::Return(0))
 }
 
+/// The receiver of the signals sent by a POSIX timer.
+enum SignalTarget {
+    Process(Weak<Process>),
+    Thread(Weak<Thread>),
+}
+
+impl SignalTarget {
+    fn enqueue_signal(&self, signal: TimerSignal) {
+        match self {
+            Self::Process(process) => {
+                if let Some(process) = process.upgrade() {
+                    process.enqueue_signal(signal);
+                }
+            }
+            Self::Thread(thread) => {
+                if let Some(thread) = thread.upgrade()
+                    && let Some(posix_thread) = thread.as_posix_thread()
+                {
+                    posix_thread.enqueue_signal(Box::new(signal));
+                }
+            }
+        }
+    }
+}
+
+fn parse_sig_num(signo: i32) -> Result<SigNum> {
+    u8::try_from(signo)
+        .ok()
+        .and_then(|signo| SigNum::try_from(signo).ok())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid signal number"))
+}
+
 /// Creates a timer associated with the specified clock ID.
 ///
 /// This timer will invoke the given callback function (`func`) when it expires.
@@ -149,8 +200,8 @@ where
                 match clock_type {
                     DynamicClockType::Profiling => process_timer_manager.create_prof_timer(func),
                     DynamicClockType::Virtual => process_timer_manager.create_virtual_timer(func),
-                    // TODO: support scheduling clock and fd clock.
-                    _ => unimplemented!(),
+                    // TODO: support scheduling clock.
+                    _ => return_errno_with_message!(Errno::EINVAL, "invalid clock id"),
                 }
             }
             DynamicClockIdInfo::Tid(tid, clock_type) => {
@@ -160,10 +211,13 @@ where
                 match clock_type {
                     DynamicClockType::Profiling => posix_thread.create_prof_timer(func),
                     DynamicClockType::Virtual => posix_thread.create_virtual_timer(func),
-                    _ => unimplemented!(),
+                    _ => return_errno_with_message!(Errno::EINVAL, "invalid clock id"),
                 }
             }
-            DynamicClockIdInfo::Fd(_) => unimplemented!(),
+            // Like Linux, the dynamic clocks of files do not support timers.
+            DynamicClockIdInfo::Fd(_) => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the clock does not support timers")
+            }
         }
     };
     Ok(timer)
@@ -5,7 +5,7 @@ use crate::{
     fs::{
         file_table::{get_file_fast, FileDesc},
         fs_resolver::{FsPath, AT_FDCWD},
-        utils::PATH_MAX,
+        utils::{InodeFlags, PATH_MAX},
     },
     prelude::*,
     process::ResourceType,
@@ -36,6 +36,14 @@ pub fn sys_truncate(path_ptr: Vaddr, len: isize, ctx: &Context) -> Result<Syscal
         let fs_path = FsPath::new(AT_FDCWD, path.as_ref())?;
         ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
     };
+    dir_dentry.check_writable_mount()?;
+    if dir_dentry
+        .inode()
+        .flags()
+        .intersects(InodeFlags::IMMUTABLE | InodeFlags::APPEND)
+    {
+        return_errno_with_message!(Errno::EPERM, "the file is immutable or append-only");
+    }
     dir_dentry.resize(len as usize)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -2,14 +2,16 @@
 
 use core::time::Duration;
 
-use super::{constants::MAX_FILENAME_LEN, SyscallReturn};
+use super::{constants::MAX_FILENAME_LEN, setxattr::is_capable, SyscallReturn};
 use crate::{
     fs::{
         file_table::FileDesc,
         fs_resolver::{FsPath, AT_FDCWD},
         path::Dentry,
+        utils::Permission,
     },
     prelude::*,
+    process::credentials::capabilities::CapSet,
     time::{clocks::RealTimeCoarseClock, timespec_t, timeval_t},
 };
 
@@ -18,6 +20,10 @@ use crate::{
 /// and times[1] represents the modification time.
 /// The `flags` argument is a bit mask that can include the following values:
 /// - `AT_SYMLINK_NOFOLLOW`: If set, the file is not dereferenced if it is a symbolic link.
+/// - `AT_EMPTY_PATH`: If set, the file referred to by `dirfd` is used if the path is empty.
+///
+/// If the pathname is NULL, the file referred to by `dirfd` is used, which is how `futimens`
+/// is implemented.
 pub fn sys_utimensat(
     dirfd: FileDesc,
     pathname_ptr: Vaddr,
@@ -109,12 +115,15 @@ struct Utimbuf {
     modtime: i64,
 }
 
-fn vfs_utimes(dentry: &Dentry, times: Option<TimeSpecPair>) -> Result<SyscallReturn> {
+fn vfs_utimes(
+    dentry: &Dentry,
+    times: Option<TimeSpecPair>,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    check_utimes_permission(dentry, times.as_ref(), ctx)?;
+
     let (atime, mtime, ctime) = match times {
         Some(times) => {
-            if !times.atime.is_valid() || !times.mtime.is_valid() {
-                return_errno_with_message!(Errno::EINVAL, "invalid time")
-            }
             let now = RealTimeCoarseClock::get().read_time();
             let atime = if times.atime.is_utime_omit() {
                 dentry.atime()
@@ -138,14 +147,55 @@ fn vfs_utimes(dentry: &Dentry, times: Option<TimeSpecPair>) -> Result<SyscallRet
         }
     };
 
-    // Update times
-    dentry.set_atime(atime);
+    // Update times. The modification time is set first because it may trigger copy-up in
+    // overlayfs, after which the access time can be set on the upper file.
     dentry.set_mtime(mtime);
+    dentry.set_atime(atime);
     dentry.set_ctime(ctime);
 
     Ok(SyscallReturn::Return(0))
 }
 
+/// Checks whether the current thread can change the timestamps of the file.
+///
+/// Like Linux, only the owner (or a thread with `CAP_FOWNER`) can set the timestamps to
+/// arbitrary values. Setting both timestamps to the current time is also allowed for threads
+/// that can write the file.
+fn check_utimes_permission(
+    dentry: &Dentry,
+    times: Option<&TimeSpecPair>,
+    ctx: &Context,
+) -> Result<()> {
+    if dentry.owner()? == ctx.posix_thread.credentials().fsuid() || is_capable(CapSet::FOWNER, ctx)
+    {
+        return Ok(());
+    }
+
+    let is_touch = times.is_none_or(|times| {
+        (times.atime.is_utime_now() || times.atime.is_utime_omit())
+            && (times.mtime.is_utime_now() || times.mtime.is_utime_omit())
+    });
+    if !is_touch {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "only the owner can set the timestamps to arbitrary values"
+        );
+    }
+
+    if is_capable(CapSet::DAC_OVERRIDE, ctx) {
+        return Ok(());
+    }
+    dentry
+        .inode()
+        .check_permission(Permission::MAY_WRITE)
+        .map_err(|_| {
+            Error::with_message(
+                Errno::EACCES,
+                "the timestamps cannot be set to the current time without write permission",
+            )
+        })
+}
+
 // Common function to handle updating file times, supporting both fd and path based operations
 fn do_utimes(
     dirfd: FileDesc,
@@ -156,14 +206,30 @@ fn do_utimes(
 ) -> Result<SyscallReturn> {
     let flags = UtimensFlags::from_bits(flags)
         .ok_or(Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    if let Some(times) = times.as_ref() {
+        if !times.atime.is_valid() || !times.mtime.is_valid() {
+            return_errno_with_message!(Errno::EINVAL, "invalid time");
+        }
+    }
 
     let pathname = if pathname_ptr == 0 {
+        // Like Linux, a NULL pathname refers to the file of `dirfd` itself.
+        if dirfd == AT_FDCWD {
+            return_errno_with_message!(Errno::EFAULT, "the pathname is NULL");
+        }
+        if flags.contains(UtimensFlags::AT_SYMLINK_NOFOLLOW) {
+            return_errno_with_message!(Errno::EINVAL, "the pathname is NULL");
+        }
         String::new()
     } else {
         let cstring = ctx
             .user_space()
             .read_cstring(pathname_ptr, MAX_FILENAME_LEN)?;
-        cstring.to_string_lossy().into_owned()
+        let pathname = cstring.to_string_lossy().into_owned();
+        if pathname.is_empty() && !flags.contains(UtimensFlags::AT_EMPTY_PATH) {
+            return_errno_with_message!(Errno::ENOENT, "the pathname is empty");
+        }
+        pathname
     };
     let dentry = {
         // Determine the file system path and the corresponding entry
@@ -176,7 +242,7 @@ fn do_utimes(
         }
     };
 
-    vfs_utimes(&dentry, times)
+    vfs_utimes(&dentry, times, ctx)
 }
 
 // Sets the access and modification times for a file,
@@ -246,5 +312,6 @@ const UTIME_OMIT: i64 = (1i64 << 30) - 2i64;
 bitflags::bitflags! {
     struct UtimensFlags: u32 {
         const AT_SYMLINK_NOFOLLOW = 0x100;
+        const AT_EMPTY_PATH = 0x1000;
     }
 }
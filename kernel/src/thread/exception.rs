@@ -2,14 +2,26 @@
 
 #![expect(unused_variables)]
 
+use align_ext::AlignExt;
 use aster_rights::Full;
-use ostd::cpu::context::{CpuExceptionInfo, UserContext};
+use ostd::{
+    cpu::{
+        context::{CpuExceptionInfo, UserContext},
+        PinCurrentCpu,
+    },
+    mm::vm_space::VmItem,
+    numa::{node_of_cpu, node_of_paddr, num_nodes},
+    task::disable_preempt,
+};
 
 use crate::{
     current_userspace,
     prelude::*,
     process::signal::signals::fault::FaultSignal,
-    vm::{page_fault_handler::PageFaultHandler, perms::VmPerms, vmar::Vmar},
+    vm::{
+        numa::queue_page_migration, page_fault_handler::PageFaultHandler, perms::VmPerms,
+        vmar::Vmar,
+    },
 };
 
 /// Page fault information converted from [`CpuExceptionInfo`].
@@ -35,6 +47,9 @@ pub fn handle_exception(ctx: &Context, context: &UserContext) {
         let user_space = ctx.user_space();
         let root_vmar = user_space.root_vmar();
         if handle_page_fault_from_vmar(root_vmar, &page_fault_info).is_ok() {
+            if num_nodes() > 1 {
+                record_numa_fault(ctx, root_vmar, page_fault_info.address);
+            }
             return;
         }
     }
@@ -57,6 +72,35 @@ fn handle_page_fault_from_vmar(
     Ok(())
 }
 
+/// Accounts a page fault to the NUMA node where the faulting page resides.
+///
+/// If the page resides on a node other than the current one, and the current
+/// node is the preferred node of the thread, the page is queued to be migrated
+/// to the current node.
+fn record_numa_fault(ctx: &Context, root_vmar: &Vmar<Full>, address: Vaddr) {
+    let page_addr = address.align_down(PAGE_SIZE);
+    let node_id = {
+        let Ok(mut cursor) = root_vmar
+            .vm_space()
+            .cursor(&(page_addr..page_addr + PAGE_SIZE))
+        else {
+            return;
+        };
+        let Ok(VmItem::Mapped { frame, .. }) = cursor.query() else {
+            return;
+        };
+        node_of_paddr(frame.start_paddr())
+    };
+
+    let sched_attr = ctx.thread.sched_attr();
+    sched_attr.record_numa_fault(node_id);
+
+    let current_node = node_of_cpu(disable_preempt().current_cpu());
+    if node_id != current_node && sched_attr.preferred_numa_node() == Some(current_node) {
+        queue_page_migration(root_vmar, page_addr, current_node);
+    }
+}
+
 /// generate a fault signal for current process.
 fn generate_fault_signal(trap_info: &CpuExceptionInfo, ctx: &Context) {
     let signal = FaultSignal::from(trap_info);
@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use ostd::{
     cpu::CpuSet,
+    sync::Waker,
     task::{Task, TaskOptions},
 };
 
@@ -12,7 +15,70 @@ use crate::{
 };
 
 /// The inner data of a kernel thread.
-struct KernelThread;
+pub struct KernelThread {
+    /// Whether the thread is interrupted.
+    is_interrupted: AtomicBool,
+    /// The waker of the thread if it is waiting in a way that can be interrupted.
+    interrupted_waker: SpinLock<Option<Arc<Waker>>>,
+}
+
+impl KernelThread {
+    fn new() -> Self {
+        Self {
+            is_interrupted: AtomicBool::new(false),
+            interrupted_waker: SpinLock::new(None),
+        }
+    }
+
+    /// Interrupts the thread.
+    ///
+    /// Until the interruption is cleared, the interruptible waits of the thread (e.g., those
+    /// performed by [`Pause`]) fail with [`EINTR`], just like those of a POSIX thread that
+    /// receives a signal.
+    ///
+    /// [`Pause`]: crate::process::signal::Pause
+    /// [`EINTR`]: crate::error::Errno::EINTR
+    pub fn interrupt(&self) {
+        self.is_interrupted.store(true, Ordering::Release);
+        if let Some(waker) = &*self.interrupted_waker.lock() {
+            waker.wake_up();
+        }
+    }
+
+    /// Clears the interruption of the thread.
+    pub fn clear_interrupted(&self) {
+        self.is_interrupted.store(false, Ordering::Release);
+    }
+
+    /// Returns whether the thread is interrupted.
+    pub fn is_interrupted(&self) -> bool {
+        self.is_interrupted.load(Ordering::Acquire)
+    }
+
+    /// Sets the waker that is woken up when the thread is interrupted.
+    pub(crate) fn set_interrupted_waker(&self, waker: Arc<Waker>) {
+        let mut interrupted_waker = self.interrupted_waker.lock();
+        assert!(interrupted_waker.is_none());
+        *interrupted_waker = Some(waker);
+    }
+
+    /// Clears the waker that is woken up when the thread is interrupted.
+    pub(crate) fn clear_interrupted_waker(&self) {
+        *self.interrupted_waker.lock() = None;
+    }
+}
+
+/// A trait to provide the `as_kernel_thread` method for threads.
+pub trait AsKernelThread {
+    /// Returns the associated [`KernelThread`].
+    fn as_kernel_thread(&self) -> Option<&KernelThread>;
+}
+
+impl AsKernelThread for Thread {
+    fn as_kernel_thread(&self) -> Option<&KernelThread> {
+        self.data().downcast_ref::<KernelThread>()
+    }
+}
 
 /// Options to create or spawn a new kernel thread.
 pub struct ThreadOptions {
@@ -61,7 +127,7 @@ impl ThreadOptions {
 
         Arc::new_cyclic(|weak_task| {
             let thread = {
-                let kernel_thread = KernelThread;
+                let kernel_thread = KernelThread::new();
                 let cpu_affinity = self.cpu_affinity;
                 let sched_policy = self.sched_policy;
                 Arc::new(Thread::new(
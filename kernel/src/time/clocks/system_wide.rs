@@ -9,7 +9,12 @@ use paste::paste;
 use spin::Once;
 
 use crate::time::{
-    self, system_time::START_TIME_AS_DURATION, timer::TimerManager, Clock, SystemTime,
+    self,
+    system_time::{
+        realtime_at, remaining_slew, set_realtime, slew_realtime, START_TIME_AS_DURATION,
+    },
+    timer::TimerManager,
+    Clock, SystemTime,
 };
 
 /// The Clock that reads the jiffies, and turn the counter into `Duration`.
@@ -28,6 +33,28 @@ impl RealTimeClock {
         CLOCK_REALTIME_INSTANCE.get().unwrap()
     }
 
+    /// Steps the real time to `time` since the Unix epoch.
+    pub fn set_time(&self, time: Duration) {
+        set_realtime(time);
+        crate::vdso::update_realtime();
+    }
+
+    /// Slews the real time by `nanos` nanoseconds, replacing the ongoing slew.
+    ///
+    /// Unlike [`Self::set_time`], the real time runs slightly faster or slower until it is
+    /// adjusted, so it never jumps backwards. This method returns the nanoseconds of the ongoing
+    /// slew that have not been applied.
+    pub fn slew_time(&self, nanos: i64) -> i64 {
+        let remaining = slew_realtime(nanos);
+        crate::vdso::update_realtime();
+        remaining
+    }
+
+    /// Returns the nanoseconds of the ongoing slew that have not been applied.
+    pub fn remaining_slew(&self) -> i64 {
+        remaining_slew()
+    }
+
     /// Get the cpu-local system-wide `TimerManager` singleton of this clock.
     pub fn timer_manager() -> &'static Arc<TimerManager> {
         let preempt_guard = disable_preempt();
@@ -62,9 +89,9 @@ impl MonotonicClock {
 
 /// `RealTimeCoarseClock` is a coarse-grained version of a real-time clock.
 ///
-/// This clock will maintain a record to `RealTimeClock`. This record
+/// This clock will maintain a record of the monotonic time. This record
 /// will be updated during each system timer interruption. Reading this clock
-/// will directly reads the value of the record instead of calculating the time
+/// will convert the record to the real time instead of calculating the time
 /// based on the clocksource. Hence it is faster but less accurate.
 ///
 /// Usually it will not be used to create a timer.
@@ -73,13 +100,18 @@ pub struct RealTimeCoarseClock {
 }
 
 impl RealTimeCoarseClock {
-    /// A reference to the current value of this clock.
+    /// A reference to the record of the monotonic time.
     fn current_ref() -> &'static Once<SpinLock<Duration>> {
         static CURRENT: Once<SpinLock<Duration>> = Once::new();
 
         &CURRENT
     }
 
+    /// Reads the record of the monotonic time.
+    fn read_record() -> Duration {
+        *Self::current_ref().get().unwrap().disable_irq().lock()
+    }
+
     /// Get the singleton of this clock.
     pub fn get() -> &'static Arc<RealTimeCoarseClock> {
         CLOCK_REALTIME_COARSE_INSTANCE.get().unwrap()
@@ -165,13 +197,13 @@ impl Clock for MonotonicClock {
 
 impl Clock for RealTimeCoarseClock {
     fn read_time(&self) -> Duration {
-        *Self::current_ref().get().unwrap().disable_irq().lock()
+        realtime_at(Self::read_record())
     }
 }
 
 impl Clock for MonotonicCoarseClock {
     fn read_time(&self) -> Duration {
-        RealTimeCoarseClock::get().read_time() - *START_TIME_AS_DURATION.get().unwrap()
+        RealTimeCoarseClock::read_record()
     }
 }
 
@@ -283,14 +315,14 @@ fn init_jiffies_clock_manager() {
 }
 
 fn update_coarse_clock() {
-    let real_time = RealTimeClock::get().read_time();
+    let monotonic_time = read_monotonic_time();
     let current = RealTimeCoarseClock::current_ref().get().unwrap();
-    *current.disable_irq().lock() = real_time;
+    *current.disable_irq().lock() = monotonic_time;
 }
 
 fn init_coarse_clock() {
-    let real_time = RealTimeClock::get().read_time();
-    RealTimeCoarseClock::current_ref().call_once(|| SpinLock::new(real_time));
+    let monotonic_time = read_monotonic_time();
+    RealTimeCoarseClock::current_ref().call_once(|| SpinLock::new(monotonic_time));
     time::softirq::register_callback(update_coarse_clock);
 }
 
@@ -316,6 +348,7 @@ pub fn init_for_ktest() {
     }
     CLOCK_REALTIME_COARSE_INSTANCE.call_once(|| Arc::new(RealTimeCoarseClock { _private: () }));
     RealTimeCoarseClock::current_ref().call_once(|| SpinLock::new(Duration::from_secs(0)));
+    START_TIME_AS_DURATION.call_once(|| Duration::from_secs(0));
     JIFFIES_TIMER_MANAGER.call_once(|| {
         let clock = JiffiesClock { _private: () };
         TimerManager::new(Arc::new(clock))
@@ -5,6 +5,7 @@
 pub use core::{timer, Clock};
 
 use ::core::time::Duration;
+pub(crate) use system_time::{realtime_at, remaining_slew};
 pub use system_time::{SystemTime, START_TIME};
 pub use timer::{Timer, TimerManager};
 
@@ -3,6 +3,7 @@
 use core::time::Duration;
 
 use aster_time::{read_monotonic_time, read_start_time};
+use ostd::sync::SpinLock;
 use spin::Once;
 use time::{Date, Month, PrimitiveDateTime, Time};
 
@@ -15,6 +16,69 @@ pub struct SystemTime(PrimitiveDateTime);
 pub static START_TIME: Once<SystemTime> = Once::new();
 pub(super) static START_TIME_AS_DURATION: Once<Duration> = Once::new();
 
+/// The offset of the real time to the start time plus the monotonic time.
+///
+/// It is changed when the real time is stepped or slewed.
+static REALTIME_OFFSET: SpinLock<RealtimeOffset> = SpinLock::new(RealtimeOffset::new());
+
+/// The maximum rate at which the real time is slewed, in parts per million.
+///
+/// Like Linux, the real time runs at most 0.05% faster or slower while it is slewed.
+const MAX_SLEW_PPM: u128 = 500;
+
+struct RealtimeOffset {
+    /// The offset in nanoseconds, excluding the ongoing slew.
+    nanos: i64,
+    /// The ongoing slew, if any.
+    slew: Option<Slew>,
+}
+
+/// A gradual adjustment of the real time by `nanos` nanoseconds, which starts at the monotonic
+/// time `start`.
+struct Slew {
+    start: Duration,
+    nanos: i64,
+}
+
+impl RealtimeOffset {
+    const fn new() -> Self {
+        Self {
+            nanos: 0,
+            slew: None,
+        }
+    }
+
+    /// Returns the offset in nanoseconds at the monotonic time `monotonic`.
+    fn nanos_at(&self, monotonic: Duration) -> i64 {
+        let slewed = self
+            .slew
+            .as_ref()
+            .map_or(0, |slew| slew.applied_nanos_at(monotonic));
+        self.nanos.saturating_add(slewed)
+    }
+
+    /// Stops the ongoing slew at the monotonic time `monotonic`.
+    ///
+    /// This method returns the nanoseconds that have not been applied.
+    fn stop_slew(&mut self, monotonic: Duration) -> i64 {
+        let Some(slew) = self.slew.take() else {
+            return 0;
+        };
+        let applied = slew.applied_nanos_at(monotonic);
+        self.nanos = self.nanos.saturating_add(applied);
+        slew.nanos - applied
+    }
+}
+
+impl Slew {
+    /// Returns the nanoseconds that have been applied at the monotonic time `monotonic`.
+    fn applied_nanos_at(&self, monotonic: Duration) -> i64 {
+        let elapsed = monotonic.saturating_sub(self.start).as_nanos();
+        let max_nanos = (elapsed * MAX_SLEW_PPM / 1_000_000).min(i64::MAX as u128) as i64;
+        self.nanos.clamp(-max_nanos, max_nanos)
+    }
+}
+
 pub(super) fn init() {
     let start_time = convert_system_time(read_start_time()).unwrap();
     START_TIME_AS_DURATION
@@ -41,10 +105,8 @@ impl SystemTime {
     /// Returns the current system time
     pub fn now() -> Self {
         // The get real time result should always be valid
-        START_TIME
-            .get()
-            .unwrap()
-            .checked_add(read_monotonic_time())
+        SystemTime::UNIX_EPOCH
+            .checked_add(realtime_at(read_monotonic_time()))
             .unwrap()
     }
 
@@ -81,6 +143,54 @@ impl SystemTime {
     }
 }
 
+/// Returns the real time since the Unix epoch when the monotonic time is `monotonic`.
+pub(crate) fn realtime_at(monotonic: Duration) -> Duration {
+    let base = *START_TIME_AS_DURATION.get().unwrap() + monotonic;
+    let offset = REALTIME_OFFSET.disable_irq().lock().nanos_at(monotonic);
+    if offset >= 0 {
+        base + Duration::from_nanos(offset as u64)
+    } else {
+        base.saturating_sub(Duration::from_nanos(offset.unsigned_abs()))
+    }
+}
+
+/// Steps the real time to `time` since the Unix epoch.
+///
+/// The ongoing slew is canceled.
+pub(super) fn set_realtime(time: Duration) {
+    let mut offset = REALTIME_OFFSET.disable_irq().lock();
+    let base = *START_TIME_AS_DURATION.get().unwrap() + read_monotonic_time();
+    let nanos = time.as_nanos() as i128 - base.as_nanos() as i128;
+    offset.nanos = nanos.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    offset.slew = None;
+}
+
+/// Slews the real time by `nanos` nanoseconds, replacing the ongoing slew.
+///
+/// This function returns the nanoseconds of the ongoing slew that have not been applied.
+pub(super) fn slew_realtime(nanos: i64) -> i64 {
+    let mut offset = REALTIME_OFFSET.disable_irq().lock();
+    let monotonic = read_monotonic_time();
+    let remaining = offset.stop_slew(monotonic);
+    if nanos != 0 {
+        offset.slew = Some(Slew {
+            start: monotonic,
+            nanos,
+        });
+    }
+    remaining
+}
+
+/// Returns the nanoseconds of the ongoing slew that have not been applied.
+pub(crate) fn remaining_slew() -> i64 {
+    let offset = REALTIME_OFFSET.disable_irq().lock();
+    let monotonic = read_monotonic_time();
+    offset
+        .slew
+        .as_ref()
+        .map_or(0, |slew| slew.nanos - slew.applied_nanos_at(monotonic))
+}
+
 /// convert ostd::time::Time to System time
 fn convert_system_time(system_time: aster_time::SystemTime) -> Result<SystemTime> {
     let month = match Month::try_from(system_time.month) {
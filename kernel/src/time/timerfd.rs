@@ -14,7 +14,7 @@ use crate::{
         signal::{PollHandle, Pollable, Pollee},
         Gid, Uid,
     },
-    syscall::create_timer,
+    syscall::{create_timer, ClockId},
     time::{clocks::RealTimeClock, Timer},
 };
 
@@ -34,9 +34,28 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// The flags used for arming a timerfd.
+    pub struct TFDSetTimeFlags: u32 {
+        /// The expiration time is an absolute value of the clock.
+        const TFD_TIMER_ABSTIME = 1 << 0;
+        /// The timer is canceled if the realtime clock is set discontinuously.
+        const TFD_TIMER_CANCEL_ON_SET = 1 << 1;
+    }
+}
+
 impl TimerfdFile {
     /// Creates a new `TimerfdFile` instance.
+    ///
+    /// Only `CLOCK_REALTIME`, `CLOCK_MONOTONIC`, and `CLOCK_BOOTTIME` are supported.
     pub fn new(clockid: clockid_t, flags: TFDFlags, ctx: &Context) -> Result<Self> {
+        if !matches!(
+            ClockId::try_from(clockid),
+            Ok(ClockId::CLOCK_REALTIME | ClockId::CLOCK_MONOTONIC | ClockId::CLOCK_BOOTTIME)
+        ) {
+            return_errno_with_message!(Errno::EINVAL, "the clock is not supported by timerfd");
+        }
+
         let ticks = Arc::new(AtomicU64::new(0));
         let pollee = Pollee::new();
 
@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The BLAKE2s hash function.
+//!
+//! Reference: <https://www.rfc-editor.org/rfc/rfc7693>
+
+const BLOCK_SIZE: usize = 64;
+
+/// The size of the digest in bytes.
+pub const DIGEST_SIZE: usize = 32;
+
+const IV: [u32; 8] = [
+    0x6A09_E667,
+    0xBB67_AE85,
+    0x3C6E_F372,
+    0xA54F_F53A,
+    0x510E_527F,
+    0x9B05_688C,
+    0x1F83_D9AB,
+    0x5BE0_CD19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The maximum size of a key in bytes.
+pub const MAX_KEY_SIZE: usize = 32;
+
+/// A BLAKE2s hasher.
+///
+/// The hasher is unkeyed with a 32-byte digest unless it is created by [`Blake2s::new_keyed`].
+#[derive(Clone)]
+pub struct Blake2s {
+    h: [u32; 8],
+    /// The number of bytes compressed so far.
+    t: u64,
+    buf: [u8; BLOCK_SIZE],
+    buf_len: usize,
+}
+
+impl Blake2s {
+    pub const fn new() -> Self {
+        Self::with_params(0, DIGEST_SIZE)
+    }
+
+    /// Creates a keyed hasher whose digest is truncated to `digest_size` bytes.
+    ///
+    /// The digest size is part of the parameter block, so a truncated digest differs from the
+    /// prefix of a full digest. The caller should take the first `digest_size` bytes of the
+    /// result of [`Self::finalize`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the key is longer than [`MAX_KEY_SIZE`] or the digest size is zero
+    /// or larger than [`DIGEST_SIZE`].
+    pub fn new_keyed(key: &[u8], digest_size: usize) -> Self {
+        assert!(key.len() <= MAX_KEY_SIZE);
+        assert!(digest_size > 0 && digest_size <= DIGEST_SIZE);
+
+        let mut hasher = Self::with_params(key.len(), digest_size);
+        if !key.is_empty() {
+            // The key is padded to a full block and compressed before the data.
+            let mut block = [0; BLOCK_SIZE];
+            block[..key.len()].copy_from_slice(key);
+            hasher.update(&block);
+        }
+        hasher
+    }
+
+    const fn with_params(key_size: usize, digest_size: usize) -> Self {
+        let mut h = IV;
+        // The parameter block: the key size, fanout and depth of one, and the digest size.
+        h[0] ^= 0x0101_0000 ^ ((key_size as u32) << 8) ^ digest_size as u32;
+        Self {
+            h,
+            t: 0,
+            buf: [0; BLOCK_SIZE],
+            buf_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // The last block must be compressed with the finalization flag, so
+            // a full buffer is only compressed once more data arrives.
+            if self.buf_len == BLOCK_SIZE {
+                self.t += BLOCK_SIZE as u64;
+                self.compress(false);
+                self.buf_len = 0;
+            }
+
+            let len = (BLOCK_SIZE - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + len].copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; DIGEST_SIZE] {
+        self.t += self.buf_len as u64;
+        self.buf[self.buf_len..].fill(0);
+        self.compress(true);
+
+        let mut digest = [0; DIGEST_SIZE];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.h) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, is_last: bool) {
+        let mut m = [0u32; 16];
+        for (word, chunk) in m.iter_mut().zip(self.buf.chunks_exact(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        let mut v = [0u32; 16];
+        v[..8].copy_from_slice(&self.h);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.t as u32;
+        v[13] ^= (self.t >> 32) as u32;
+        if is_last {
+            v[14] = !v[14];
+        }
+
+        for s in SIGMA.iter() {
+            g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+
+        for i in 0..8 {
+            self.h[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+/// The mixing function G.
+fn g(v: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn hash(data: &[u8]) -> [u8; DIGEST_SIZE] {
+        let mut hasher = Blake2s::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    #[ktest]
+    fn test_vectors() {
+        assert_eq!(
+            hash(b""),
+            [
+                0x69, 0x21, 0x7a, 0x30, 0x79, 0x90, 0x80, 0x94, 0xe1, 0x11, 0x21, 0xd0, 0x42, 0x35,
+                0x4a, 0x7c, 0x1f, 0x55, 0xb6, 0x48, 0x2c, 0xa1, 0xa5, 0x1e, 0x1b, 0x25, 0x0d, 0xfd,
+                0x1e, 0xd0, 0xee, 0xf9,
+            ]
+        );
+        assert_eq!(
+            hash(b"abc"),
+            [
+                0x50, 0x8c, 0x5e, 0x8c, 0x32, 0x7c, 0x14, 0xe2, 0xe1, 0xa7, 0x2b, 0xa3, 0x4e, 0xeb,
+                0x45, 0x2f, 0x37, 0x45, 0x8b, 0x20, 0x9e, 0xd6, 0x3a, 0x29, 0x4d, 0x99, 0x9b, 0x4c,
+                0x86, 0x67, 0x59, 0x82,
+            ]
+        );
+    }
+
+    /// The test vector in Appendix B of RFC 7693 and the keyed test vector of the reference
+    /// implementation, whose key and data are `0x00, 0x01, ...`.
+    #[ktest]
+    fn test_keyed_vectors() {
+        let mut hasher = Blake2s::new_keyed(&[], DIGEST_SIZE);
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), hash(b"abc"));
+
+        let key: Vec<u8> = (0..MAX_KEY_SIZE as u8).collect();
+        let data: Vec<u8> = (0..64).collect();
+        let mut hasher = Blake2s::new_keyed(&key, DIGEST_SIZE);
+        hasher.update(&data);
+        assert_eq!(
+            hasher.finalize(),
+            [
+                0x89, 0x75, 0xb0, 0x57, 0x7f, 0xd3, 0x55, 0x66, 0xd7, 0x50, 0xb3, 0x62, 0xb0, 0x89,
+                0x7a, 0x26, 0xc3, 0x99, 0x13, 0x6d, 0xf0, 0x7b, 0xab, 0xab, 0xbd, 0xe6, 0x20, 0x3f,
+                0xf2, 0x95, 0x4e, 0xd4,
+            ]
+        );
+    }
+
+    #[ktest]
+    fn test_split_updates() {
+        let data: Vec<u8> = (0..200).collect();
+
+        let mut hasher = Blake2s::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        let digest = hasher.finalize();
+        assert_eq!(digest, hash(&data));
+        assert_eq!(
+            digest,
+            [
+                0x6d, 0x24, 0x4e, 0x1a, 0x06, 0xce, 0x4e, 0xf5, 0x78, 0xdd, 0x0f, 0x63, 0xaf, 0xf0,
+                0x93, 0x67, 0x06, 0x73, 0x51, 0x19, 0xca, 0x9c, 0x8d, 0x22, 0xd8, 0x6c, 0x80, 0x14,
+                0x14, 0xab, 0x97, 0x41
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ChaCha20-Poly1305 AEAD construction.
+//!
+//! The 96-bit nonce of RFC 8439 consists of 32 zero bits followed by a 64-bit
+//! counter in little endian (see [`super::chacha`]).
+//!
+//! Reference: <https://www.rfc-editor.org/rfc/rfc8439>
+
+use super::{
+    chacha::{chacha20_block, BLOCK_SIZE},
+    poly1305::{self, Poly1305},
+};
+
+/// The size of a key in bytes.
+pub const KEY_SIZE: usize = super::chacha::KEY_SIZE;
+
+/// The size of a tag in bytes.
+pub const TAG_SIZE: usize = poly1305::TAG_SIZE;
+
+/// Encrypts `data` in place and returns the tag that authenticates it and `aad`.
+pub fn seal(key: &[u8; KEY_SIZE], nonce: u64, aad: &[u8], data: &mut [u8]) -> [u8; TAG_SIZE] {
+    apply_keystream(key, nonce, data);
+    compute_tag(key, nonce, aad, data)
+}
+
+/// Decrypts `data` in place if `tag` authenticates it and `aad`.
+///
+/// Returns whether the tag is valid. If it is not, `data` is left untouched.
+#[must_use]
+pub fn open(
+    key: &[u8; KEY_SIZE],
+    nonce: u64,
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8; TAG_SIZE],
+) -> bool {
+    let expected_tag = compute_tag(key, nonce, aad, data);
+    if !ct_eq(&expected_tag, tag) {
+        return false;
+    }
+
+    apply_keystream(key, nonce, data);
+    true
+}
+
+fn apply_keystream(key: &[u8; KEY_SIZE], nonce: u64, data: &mut [u8]) {
+    // The first block is reserved for the one-time key of Poly1305.
+    for (counter, chunk) in (1..).zip(data.chunks_mut(BLOCK_SIZE)) {
+        let block = chacha20_block(key, counter, nonce);
+        for (byte, key_byte) in chunk.iter_mut().zip(block) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+fn compute_tag(key: &[u8; KEY_SIZE], nonce: u64, aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+    let block = chacha20_block(key, 0, nonce);
+    let mut mac = Poly1305::new(block[..poly1305::KEY_SIZE].try_into().unwrap());
+
+    mac.update(aad);
+    mac.pad();
+    mac.update(ciphertext);
+    mac.pad();
+    mac.update(&(aad.len() as u64).to_le_bytes());
+    mac.update(&(ciphertext.len() as u64).to_le_bytes());
+
+    mac.finalize()
+}
+
+/// Compares two byte arrays in constant time.
+fn ct_eq(lhs: &[u8; TAG_SIZE], rhs: &[u8; TAG_SIZE]) -> bool {
+    let diff = lhs
+        .iter()
+        .zip(rhs.iter())
+        .fold(0, |diff, (lhs, rhs)| diff | (lhs ^ rhs));
+    core::hint::black_box(diff) == 0
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// The test vector in Section 2.8.2 of RFC 8439, whose nonce is changed to have 32 leading
+    /// zero bits.
+    #[ktest]
+    fn test_seal_open() {
+        let key: [u8; KEY_SIZE] = core::array::from_fn(|i| 0x80 + i as u8);
+        let nonce = 0x4746_4544_4342_4140;
+        let aad = [
+            0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7,
+        ];
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut data = plaintext.to_vec();
+        let tag = seal(&key, nonce, &aad, &mut data);
+        assert_eq!(
+            data[..16],
+            [
+                0xa4, 0x79, 0xcb, 0x54, 0x62, 0x89, 0x46, 0xd6, 0xf4, 0x04, 0x2a, 0x8e, 0x38, 0x4e,
+                0xf4, 0xbd,
+            ]
+        );
+        assert_eq!(
+            tag,
+            [
+                0x2d, 0xbf, 0x18, 0x9b, 0x66, 0x8b, 0xd4, 0x30, 0xae, 0xf9, 0x14, 0x7e, 0x99, 0xcb,
+                0x6c, 0x89,
+            ]
+        );
+
+        let mut bad_tag = tag;
+        bad_tag[0] ^= 1;
+        assert!(!open(&key, nonce, &aad, &mut data, &bad_tag));
+        assert!(open(&key, nonce, &aad, &mut data, &tag));
+        assert_eq!(data, plaintext);
+    }
+}
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The X25519 Diffie-Hellman function.
+//!
+//! The field elements are represented by five 51-bit limbs, so that the products
+//! fit in 128 bits. The Montgomery ladder runs in constant time with respect to
+//! the secret scalar.
+//!
+//! Reference: <https://www.rfc-editor.org/rfc/rfc7748>
+
+/// The size of a scalar or a point in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// The u-coordinate of the base point.
+const BASE_POINT: [u8; KEY_SIZE] = {
+    let mut point = [0; KEY_SIZE];
+    point[0] = 9;
+    point
+};
+
+/// Computes the X25519 function of `scalar` and the u-coordinate `point`.
+///
+/// The scalar is clamped before use, so any 32 bytes are a valid private key.
+pub fn x25519(scalar: &[u8; KEY_SIZE], point: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    let mut scalar = *scalar;
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+
+    let x1 = Fe::from_bytes(point);
+    let mut x2 = Fe::ONE;
+    let mut z2 = Fe::ZERO;
+    let mut x3 = x1;
+    let mut z3 = Fe::ONE;
+    let mut swap = 0;
+
+    for t in (0..255).rev() {
+        let bit = ((scalar[t / 8] >> (t % 8)) & 1) as u64;
+        swap ^= bit;
+        Fe::cswap(swap, &mut x2, &mut x3);
+        Fe::cswap(swap, &mut z2, &mut z3);
+        swap = bit;
+
+        let a = x2.add(&z2);
+        let aa = a.square();
+        let b = x2.sub(&z2);
+        let bb = b.square();
+        let e = aa.sub(&bb);
+        let c = x3.add(&z3);
+        let d = x3.sub(&z3);
+        let da = d.mul(&a);
+        let cb = c.mul(&b);
+        x3 = da.add(&cb).square();
+        z3 = x1.mul(&da.sub(&cb).square());
+        x2 = aa.mul(&bb);
+        z2 = e.mul(&aa.add(&A24.mul(&e)));
+    }
+    Fe::cswap(swap, &mut x2, &mut x3);
+    Fe::cswap(swap, &mut z2, &mut z3);
+
+    x2.mul(&z2.invert()).to_bytes()
+}
+
+/// Computes the public key of the private key `scalar`.
+pub fn x25519_base(scalar: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    x25519(scalar, &BASE_POINT)
+}
+
+/// An element of the field `GF(2^255 - 19)`.
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+const LIMB_MASK: u64 = (1 << 51) - 1;
+
+/// The constant `(486662 - 2) / 4`.
+const A24: Fe = Fe([121665, 0, 0, 0, 0]);
+
+impl Fe {
+    const ZERO: Fe = Fe([0; 5]);
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    fn from_bytes(bytes: &[u8; KEY_SIZE]) -> Self {
+        let load = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        // The most significant bit is ignored.
+        Self([
+            load(0) & LIMB_MASK,
+            (load(6) >> 3) & LIMB_MASK,
+            (load(12) >> 6) & LIMB_MASK,
+            (load(19) >> 1) & LIMB_MASK,
+            (load(24) >> 12) & LIMB_MASK,
+        ])
+    }
+
+    fn to_bytes(self) -> [u8; KEY_SIZE] {
+        let mut h = self.carry().0;
+
+        // Compute `q`, which is one if `h >= p` or zero otherwise, and then `h - q * p`.
+        let mut q = (h[0] + 19) >> 51;
+        q = (h[1] + q) >> 51;
+        q = (h[2] + q) >> 51;
+        q = (h[3] + q) >> 51;
+        q = (h[4] + q) >> 51;
+
+        h[0] += 19 * q;
+        h[1] += h[0] >> 51;
+        h[0] &= LIMB_MASK;
+        h[2] += h[1] >> 51;
+        h[1] &= LIMB_MASK;
+        h[3] += h[2] >> 51;
+        h[2] &= LIMB_MASK;
+        h[4] += h[3] >> 51;
+        h[3] &= LIMB_MASK;
+        // This discards the bit 255, i.e., subtracts `2^255`.
+        h[4] &= LIMB_MASK;
+
+        let words = [
+            h[0] | (h[1] << 51),
+            (h[1] >> 13) | (h[2] << 38),
+            (h[2] >> 26) | (h[3] << 25),
+            (h[3] >> 39) | (h[4] << 12),
+        ];
+        let mut bytes = [0; KEY_SIZE];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reduces the limbs to 51 bits, except that the lowest limb may be slightly larger.
+    fn carry(self) -> Self {
+        let mut h = self.0;
+
+        h[1] += h[0] >> 51;
+        h[0] &= LIMB_MASK;
+        h[2] += h[1] >> 51;
+        h[1] &= LIMB_MASK;
+        h[3] += h[2] >> 51;
+        h[2] &= LIMB_MASK;
+        h[4] += h[3] >> 51;
+        h[3] &= LIMB_MASK;
+        h[0] += (h[4] >> 51) * 19;
+        h[4] &= LIMB_MASK;
+
+        Self(h)
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        let mut h = self.0;
+        for (limb, rhs_limb) in h.iter_mut().zip(rhs.0) {
+            *limb += rhs_limb;
+        }
+        Self(h)
+    }
+
+    fn sub(&self, rhs: &Self) -> Self {
+        // Add `2 * p` first to avoid underflows, since the limbs of `rhs` are at most 52 bits.
+        const TWO_P: [u64; 5] = [
+            0xf_ffff_ffff_ffda,
+            0xf_ffff_ffff_fffe,
+            0xf_ffff_ffff_fffe,
+            0xf_ffff_ffff_fffe,
+            0xf_ffff_ffff_fffe,
+        ];
+
+        let mut h = self.0;
+        for ((limb, rhs_limb), two_p_limb) in h.iter_mut().zip(rhs.0).zip(TWO_P) {
+            *limb = *limb + two_p_limb - rhs_limb;
+        }
+        Self(h).carry()
+    }
+
+    fn mul(&self, rhs: &Self) -> Self {
+        let [a0, a1, a2, a3, a4] = self.0;
+        let [b0, b1, b2, b3, b4] = rhs.0;
+        let m = |x: u64, y: u64| x as u128 * y as u128;
+
+        // `2^255 = 19 (mod p)`, so the limbs above 2^255 are folded with a factor of 19.
+        let (b1_19, b2_19, b3_19, b4_19) = (b1 * 19, b2 * 19, b3 * 19, b4 * 19);
+
+        let c0 = m(a0, b0) + m(a1, b4_19) + m(a2, b3_19) + m(a3, b2_19) + m(a4, b1_19);
+        let mut c1 = m(a0, b1) + m(a1, b0) + m(a2, b4_19) + m(a3, b3_19) + m(a4, b2_19);
+        let mut c2 = m(a0, b2) + m(a1, b1) + m(a2, b0) + m(a3, b4_19) + m(a4, b3_19);
+        let mut c3 = m(a0, b3) + m(a1, b2) + m(a2, b1) + m(a3, b0) + m(a4, b4_19);
+        let mut c4 = m(a0, b4) + m(a1, b3) + m(a2, b2) + m(a3, b1) + m(a4, b0);
+
+        c1 += c0 >> 51;
+        c2 += c1 >> 51;
+        c3 += c2 >> 51;
+        c4 += c3 >> 51;
+
+        // `c4` contains no factor of 19, so the carry multiplied by 19 fits in 64 bits.
+        let mut h = [
+            c0 as u64 & LIMB_MASK,
+            c1 as u64 & LIMB_MASK,
+            c2 as u64 & LIMB_MASK,
+            c3 as u64 & LIMB_MASK,
+            c4 as u64 & LIMB_MASK,
+        ];
+        h[0] += (c4 >> 51) as u64 * 19;
+        h[1] += h[0] >> 51;
+        h[0] &= LIMB_MASK;
+
+        Self(h)
+    }
+
+    fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    /// Computes the inverse by raising to the power of `p - 2`.
+    fn invert(&self) -> Self {
+        // `p - 2 = 2^255 - 21`, whose bits are all ones except the bits 2 and 4.
+        let mut res = Fe::ONE;
+        for t in (0..255).rev() {
+            res = res.square();
+            if t != 2 && t != 4 {
+                res = res.mul(self);
+            }
+        }
+        res
+    }
+
+    /// Swaps `a` and `b` if `swap` is one, in constant time.
+    fn cswap(swap: u64, a: &mut Self, b: &mut Self) {
+        let mask = 0u64.wrapping_sub(swap);
+        for (a_limb, b_limb) in a.0.iter_mut().zip(b.0.iter_mut()) {
+            let diff = mask & (*a_limb ^ *b_limb);
+            *a_limb ^= diff;
+            *b_limb ^= diff;
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// The test vector in Section 5.2 of RFC 7748.
+    #[ktest]
+    fn test_rfc7748_vector() {
+        let scalar = [
+            0xa5, 0x46, 0xe3, 0x6b, 0xf0, 0x52, 0x7c, 0x9d, 0x3b, 0x16, 0x15, 0x4b, 0x82, 0x46,
+            0x5e, 0xdd, 0x62, 0x14, 0x4c, 0x0a, 0xc1, 0xfc, 0x5a, 0x18, 0x50, 0x6a, 0x22, 0x44,
+            0xba, 0x44, 0x9a, 0xc4,
+        ];
+        let point = [
+            0xe6, 0xdb, 0x68, 0x67, 0x58, 0x30, 0x30, 0xdb, 0x35, 0x94, 0xc1, 0xa4, 0x24, 0xb1,
+            0x5f, 0x7c, 0x72, 0x66, 0x24, 0xec, 0x26, 0xb3, 0x35, 0x3b, 0x10, 0xa9, 0x03, 0xa6,
+            0xd0, 0xab, 0x1c, 0x4c,
+        ];
+        assert_eq!(
+            x25519(&scalar, &point),
+            [
+                0xc3, 0xda, 0x55, 0x37, 0x9d, 0xe9, 0xc6, 0x90, 0x8e, 0x94, 0xea, 0x4d, 0xf2, 0x8d,
+                0x08, 0x4f, 0x32, 0xec, 0xcf, 0x03, 0x49, 0x1c, 0x71, 0xf7, 0x54, 0xb4, 0x07, 0x55,
+                0x77, 0xa2, 0x85, 0x52,
+            ]
+        );
+    }
+
+    /// The Diffie-Hellman test vector in Section 6.1 of RFC 7748.
+    #[ktest]
+    fn test_diffie_hellman() {
+        let alice_private = [
+            0x77, 0x07, 0x6d, 0x0a, 0x73, 0x18, 0xa5, 0x7d, 0x3c, 0x16, 0xc1, 0x72, 0x51, 0xb2,
+            0x66, 0x45, 0xdf, 0x4c, 0x2f, 0x87, 0xeb, 0xc0, 0x99, 0x2a, 0xb1, 0x77, 0xfb, 0xa5,
+            0x1d, 0xb9, 0x2c, 0x2a,
+        ];
+        let bob_private = [
+            0x5d, 0xab, 0x08, 0x7e, 0x62, 0x4a, 0x8a, 0x4b, 0x79, 0xe1, 0x7f, 0x8b, 0x83, 0x80,
+            0x0e, 0xe6, 0x6f, 0x3b, 0xb1, 0x29, 0x26, 0x18, 0xb6, 0xfd, 0x1c, 0x2f, 0x8b, 0x27,
+            0xff, 0x88, 0xe0, 0xeb,
+        ];
+
+        let alice_public = x25519_base(&alice_private);
+        assert_eq!(
+            alice_public,
+            [
+                0x85, 0x20, 0xf0, 0x09, 0x89, 0x30, 0xa7, 0x54, 0x74, 0x8b, 0x7d, 0xdc, 0xb4, 0x3e,
+                0xf7, 0x5a, 0x0d, 0xbf, 0x3a, 0x0d, 0x26, 0x38, 0x1a, 0xf4, 0xeb, 0xa4, 0xa9, 0x8e,
+                0xaa, 0x9b, 0x4e, 0x6a,
+            ]
+        );
+
+        let bob_public = x25519_base(&bob_private);
+        let shared = x25519(&alice_private, &bob_public);
+        assert_eq!(shared, x25519(&bob_private, &alice_public));
+        assert_eq!(
+            shared,
+            [
+                0x4a, 0x5d, 0x9d, 0x5b, 0xa4, 0xce, 0x2d, 0xe1, 0x72, 0x8e, 0x3b, 0xf4, 0x80, 0x35,
+                0x0f, 0x25, 0xe0, 0x7e, 0x21, 0xc9, 0x47, 0xd1, 0x9e, 0x33, 0x76, 0xf0, 0x9b, 0x3c,
+                0x1e, 0x16, 0x17, 0x42,
+            ]
+        );
+    }
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Cryptographic primitives.
+//!
+//! The primitives are implemented in software and used by the kernel itself (e.g.,
+//! by the random number generator and WireGuard), so they are not exposed to user
+//! space.
+
+pub mod blake2s;
+pub mod chacha;
+pub mod chacha20poly1305;
+pub mod curve25519;
+pub mod poly1305;
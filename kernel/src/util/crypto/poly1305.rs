@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Poly1305 one-time authenticator.
+//!
+//! The accumulator and the key are represented by five 26-bit limbs, so that the
+//! products fit in 64 bits.
+//!
+//! Reference: <https://www.rfc-editor.org/rfc/rfc8439>
+
+/// The size of a key in bytes.
+pub const KEY_SIZE: usize = 32;
+
+/// The size of a tag in bytes.
+pub const TAG_SIZE: usize = 16;
+
+const BLOCK_SIZE: usize = 16;
+
+const LIMB_MASK: u32 = (1 << 26) - 1;
+
+/// A Poly1305 authenticator.
+pub struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    s: [u32; 4],
+    buf: [u8; BLOCK_SIZE],
+    buf_len: usize,
+}
+
+impl Poly1305 {
+    /// Creates an authenticator with a one-time key.
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        let le = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+
+        // Clamp `r` as required, i.e., `r &= 0x0ffffffc0ffffffc0ffffffc0fffffff`.
+        let r = [
+            le(0) & 0x03ff_ffff,
+            (le(3) >> 2) & 0x03ff_ff03,
+            (le(6) >> 4) & 0x03ff_c0ff,
+            (le(9) >> 6) & 0x03f0_3fff,
+            (le(12) >> 8) & 0x000f_ffff,
+        ];
+        let s = [le(16), le(20), le(24), le(28)];
+
+        Self {
+            r,
+            h: [0; 5],
+            s,
+            buf: [0; BLOCK_SIZE],
+            buf_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buf_len > 0 {
+            let len = (BLOCK_SIZE - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + len].copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+
+            if self.buf_len < BLOCK_SIZE {
+                return;
+            }
+            let block = self.buf;
+            self.process_block(&block, 1 << 24);
+            self.buf_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.process_block(block.try_into().unwrap(), 1 << 24);
+        }
+
+        let remainder = blocks.remainder();
+        self.buf[..remainder.len()].copy_from_slice(remainder);
+        self.buf_len = remainder.len();
+    }
+
+    /// Feeds zeros until the length of the data is a multiple of 16 bytes.
+    ///
+    /// This is how the data is padded in the AEAD construction.
+    pub fn pad(&mut self) {
+        if self.buf_len > 0 {
+            self.update(&[0; BLOCK_SIZE][self.buf_len..]);
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; TAG_SIZE] {
+        if self.buf_len > 0 {
+            // The last partial block is padded with a one and then zeros, instead of the high bit.
+            let mut block = [0; BLOCK_SIZE];
+            block[..self.buf_len].copy_from_slice(&self.buf[..self.buf_len]);
+            block[self.buf_len] = 1;
+            self.process_block(&block, 0);
+        }
+
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+
+        // Fully carry `h`.
+        let mut c = h1 >> 26;
+        h1 &= LIMB_MASK;
+        h2 += c;
+        c = h2 >> 26;
+        h2 &= LIMB_MASK;
+        h3 += c;
+        c = h3 >> 26;
+        h3 &= LIMB_MASK;
+        h4 += c;
+        c = h4 >> 26;
+        h4 &= LIMB_MASK;
+        h0 += c * 5;
+        c = h0 >> 26;
+        h0 &= LIMB_MASK;
+        h1 += c;
+
+        // Compute `g = h - p = h + 5 - 2^130`.
+        let mut g0 = h0 + 5;
+        c = g0 >> 26;
+        g0 &= LIMB_MASK;
+        let mut g1 = h1 + c;
+        c = g1 >> 26;
+        g1 &= LIMB_MASK;
+        let mut g2 = h2 + c;
+        c = g2 >> 26;
+        g2 &= LIMB_MASK;
+        let mut g3 = h3 + c;
+        c = g3 >> 26;
+        g3 &= LIMB_MASK;
+        let g4 = (h4 + c).wrapping_sub(1 << 26);
+
+        // Select `g` if `h >= p` (i.e., `g` does not underflow), or `h` otherwise, in constant
+        // time.
+        let mask = (g4 >> 31).wrapping_sub(1);
+        h0 = (h0 & !mask) | (g0 & mask);
+        h1 = (h1 & !mask) | (g1 & mask);
+        h2 = (h2 & !mask) | (g2 & mask);
+        h3 = (h3 & !mask) | (g3 & mask);
+        h4 = (h4 & !mask) | (g4 & mask);
+
+        // Compute `(h + s) % 2^128`.
+        let words = [
+            h0 | (h1 << 26),
+            (h1 >> 6) | (h2 << 20),
+            (h2 >> 12) | (h3 << 14),
+            (h3 >> 18) | (h4 << 8),
+        ];
+        let mut tag = [0; TAG_SIZE];
+        let mut carry = 0u64;
+        for ((chunk, word), s) in tag.chunks_exact_mut(4).zip(words).zip(self.s) {
+            let sum = word as u64 + s as u64 + carry;
+            chunk.copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        tag
+    }
+
+    /// Computes `h = (h + block) * r % p`, where `hibit` is the bit above the block.
+    fn process_block(&mut self, block: &[u8; BLOCK_SIZE], hibit: u32) {
+        let le = |i: usize| u32::from_le_bytes(block[i..i + 4].try_into().unwrap());
+
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+
+        let h0 = (self.h[0] + (le(0) & LIMB_MASK)) as u64;
+        let h1 = (self.h[1] + ((le(3) >> 2) & LIMB_MASK)) as u64;
+        let h2 = (self.h[2] + ((le(6) >> 4) & LIMB_MASK)) as u64;
+        let h3 = (self.h[3] + ((le(9) >> 6) & LIMB_MASK)) as u64;
+        let h4 = (self.h[4] + ((le(12) >> 8) | hibit)) as u64;
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        // Partially carry `h`.
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let c = (d4 >> 26) as u32;
+
+        let mut h0 = (d0 as u32 & LIMB_MASK) + c * 5;
+        let h1 = (d1 as u32 & LIMB_MASK) + (h0 >> 26);
+        h0 &= LIMB_MASK;
+
+        self.h = [
+            h0,
+            h1,
+            d2 as u32 & LIMB_MASK,
+            d3 as u32 & LIMB_MASK,
+            d4 as u32 & LIMB_MASK,
+        ];
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// The test vector in Section 2.5.2 of RFC 8439.
+    #[ktest]
+    fn test_rfc8439_vector() {
+        let key = [
+            0x85, 0xd6, 0xbe, 0x78, 0x57, 0x55, 0x6d, 0x33, 0x7f, 0x44, 0x52, 0xfe, 0x42, 0xd5,
+            0x06, 0xa8, 0x01, 0x03, 0x80, 0x8a, 0xfb, 0x0d, 0xb2, 0xfd, 0x4a, 0xbf, 0xf6, 0xaf,
+            0x41, 0x49, 0xf5, 0x1b,
+        ];
+        let expected = [
+            0xa8, 0x06, 0x1d, 0xc1, 0x30, 0x51, 0x36, 0xc6, 0xc2, 0x2b, 0x8b, 0xaf, 0x0c, 0x01,
+            0x27, 0xa9,
+        ];
+        let data = b"Cryptographic Forum Research Group";
+
+        let mut mac = Poly1305::new(&key);
+        mac.update(data);
+        assert_eq!(mac.finalize(), expected);
+
+        let mut mac = Poly1305::new(&key);
+        for chunk in data.chunks(5) {
+            mac.update(chunk);
+        }
+        assert_eq!(mac.finalize(), expected);
+    }
+
+    /// The test vectors #5 and #6 in Appendix A.3 of RFC 8439, where `h` or `h + s` must be
+    /// reduced at the end.
+    #[ktest]
+    fn test_final_reduction() {
+        let mut expected = [0; TAG_SIZE];
+        expected[0] = 3;
+
+        let mut key = [0; KEY_SIZE];
+        key[0] = 2;
+        let mut mac = Poly1305::new(&key);
+        mac.update(&[0xff; 16]);
+        assert_eq!(mac.finalize(), expected);
+
+        key[16..].fill(0xff);
+        let mut data = [0; 16];
+        data[0] = 2;
+        let mut mac = Poly1305::new(&key);
+        mac.update(&data);
+        assert_eq!(mac.finalize(), expected);
+    }
+}
@@ -83,6 +83,20 @@ fn copy_iovs_and_convert<'a, T: 'a>(
     Ok(v.into_boxed_slice())
 }
 
+/// Reads user-provided IO vectors as the base addresses and the lengths of the buffers.
+///
+/// Unlike [`VmReaderArray`] and [`VmWriterArray`], this is useful when the buffers are accessed
+/// later, possibly not from the current task.
+pub fn read_io_vecs_from_user(
+    user_space: &CurrentUserSpace,
+    start_addr: Vaddr,
+    count: usize,
+) -> Result<Box<[(Vaddr, usize)]>> {
+    copy_iovs_and_convert(user_space, start_addr, count, |iov, _| {
+        Ok((iov.base, iov.len))
+    })
+}
+
 /// A collection of [`VmReader`]s.
 ///
 /// Such readers are built from user-provided buffer, so it's always fallible.
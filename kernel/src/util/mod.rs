@@ -1,8 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod crypto;
 mod iovec;
 pub mod net;
 pub mod random;
 pub mod ring_buffer;
 
-pub use iovec::{MultiRead, MultiWrite, VmReaderArray, VmWriterArray};
+pub use iovec::{read_io_vecs_from_user, MultiRead, MultiWrite, VmReaderArray, VmWriterArray};
@@ -4,7 +4,13 @@ use core::cmp::min;
 
 use ostd::task::Task;
 
-use super::{ip::CSocketAddrInet, netlink::CSocketAddrNetlink, unix, vsock::CSocketAddrVm};
+use super::{
+    ip::{CSocketAddrInet, CSocketAddrInet6},
+    netlink::CSocketAddrNetlink,
+    packet::CSocketAddrLinkLayer,
+    unix,
+    vsock::CSocketAddrVm,
+};
 use crate::{current_userspace, net::socket::SocketAddr, prelude::*};
 
 /// Address family.
@@ -150,6 +156,30 @@ pub fn read_socket_addr_from_user(addr: Vaddr, addr_len: usize) -> Result<Socket
         &mut VmWriter::from(&mut storage.as_bytes_mut()[..addr_len]),
     )?;
 
+    parse_socket_addr(&storage, addr_len)
+}
+
+/// Parses a socket address from the bytes of its C representation.
+///
+/// This method is similar to [`read_socket_addr_from_user`], except that the bytes are already
+/// in kernel space (e.g., in the payload of a netlink attribute).
+pub fn socket_addr_from_c_bytes(bytes: &[u8]) -> Result<SocketAddr> {
+    let addr_len = bytes.len();
+    if addr_len > ADDR_MAX_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the socket address length is too large");
+    }
+
+    if addr_len < 2 {
+        return_errno_with_message!(Errno::EINVAL, "the socket address length is too small");
+    }
+
+    let mut storage = Storage::new_zeroed();
+    storage.as_bytes_mut()[..addr_len].copy_from_slice(bytes);
+
+    parse_socket_addr(&storage, addr_len)
+}
+
+fn parse_socket_addr(storage: &Storage, addr_len: usize) -> Result<SocketAddr> {
     let result = match CSocketAddrFamily::try_from(storage.sa_family as i32) {
         Ok(CSocketAddrFamily::AF_INET) => {
             if addr_len < size_of::<CSocketAddrInet>() {
@@ -158,6 +188,13 @@ pub fn read_socket_addr_from_user(addr: Vaddr, addr_len: usize) -> Result<Socket
             let (addr, port) = CSocketAddrInet::from_bytes(storage.as_bytes()).into();
             SocketAddr::IPv4(addr, port)
         }
+        Ok(CSocketAddrFamily::AF_INET6) => {
+            if addr_len < size_of::<CSocketAddrInet6>() {
+                return_errno_with_message!(Errno::EINVAL, "the socket address length is too small");
+            }
+            let (addr, port) = CSocketAddrInet6::from_bytes(storage.as_bytes()).into();
+            SocketAddr::IPv6(addr, port)
+        }
         Ok(CSocketAddrFamily::AF_UNIX) => {
             let addr = unix::from_c_bytes(&storage.as_bytes()[..addr_len])?;
             SocketAddr::Unix(addr)
@@ -169,6 +206,13 @@ pub fn read_socket_addr_from_user(addr: Vaddr, addr_len: usize) -> Result<Socket
             let addr = CSocketAddrNetlink::from_bytes(storage.as_bytes());
             SocketAddr::Netlink(addr.into())
         }
+        Ok(CSocketAddrFamily::AF_PACKET) => {
+            if addr_len < size_of::<CSocketAddrLinkLayer>() {
+                return_errno_with_message!(Errno::EINVAL, "the socket address length is too small");
+            }
+            let addr = CSocketAddrLinkLayer::from_bytes(storage.as_bytes());
+            SocketAddr::Packet(addr.into())
+        }
         Ok(CSocketAddrFamily::AF_VSOCK) => {
             if addr_len < size_of::<CSocketAddrVm>() {
                 return_errno_with_message!(Errno::EINVAL, "the socket address length is too small");
@@ -251,6 +295,11 @@ pub fn write_socket_addr_with_max_len(
             dest,
             max_len as usize,
         )?,
+        SocketAddr::IPv6(addr, port) => write_c_socket_address_util::<CSocketAddrInet6, _>(
+            (*addr, *port),
+            dest,
+            max_len as usize,
+        )?,
         SocketAddr::Unix(addr) => unix::into_c_bytes_and(addr, |bytes| {
             let written_len = min(bytes.len(), max_len as _);
             current_userspace!().write_bytes(dest, &mut VmReader::from(&bytes[..written_len]))?;
@@ -262,11 +311,34 @@ pub fn write_socket_addr_with_max_len(
         SocketAddr::Vsock(addr) => {
             write_c_socket_address_util::<CSocketAddrVm, _>(*addr, dest, max_len as usize)?
         }
+        SocketAddr::Packet(addr) => {
+            write_c_socket_address_util::<CSocketAddrLinkLayer, _>(*addr, dest, max_len as usize)?
+        }
     };
 
     Ok(actual_len as i32)
 }
 
+/// Converts a socket address to the bytes of the corresponding Linux C structure.
+///
+/// This is useful when the socket address cannot be written to the user space of the current
+/// task directly.
+///
+/// # Panics
+///
+/// This method will panic if the socket address cannot be validly mapped to the corresponding
+/// Linux C structures, as [`write_socket_addr_with_max_len`] does.
+pub fn socket_addr_to_c_bytes(socket_addr: &SocketAddr) -> Vec<u8> {
+    match socket_addr {
+        SocketAddr::IPv4(addr, port) => CSocketAddrInet::from((*addr, *port)).as_bytes().to_vec(),
+        SocketAddr::IPv6(addr, port) => CSocketAddrInet6::from((*addr, *port)).as_bytes().to_vec(),
+        SocketAddr::Unix(addr) => unix::into_c_bytes_and(addr, |bytes| bytes.to_vec()),
+        SocketAddr::Netlink(addr) => CSocketAddrNetlink::from(*addr).as_bytes().to_vec(),
+        SocketAddr::Vsock(addr) => CSocketAddrVm::from(*addr).as_bytes().to_vec(),
+        SocketAddr::Packet(addr) => CSocketAddrLinkLayer::from(*addr).as_bytes().to_vec(),
+    }
+}
+
 // Utility function to write a C socket address to user space.
 fn write_c_socket_address_util<TCSockAddr: Pod, TSockAddr>(
     addr: TSockAddr,
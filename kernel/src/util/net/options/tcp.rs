@@ -4,8 +4,8 @@ use super::RawSocketOption;
 use crate::{
     impl_raw_socket_option,
     net::socket::ip::stream::options::{
-        Congestion, DeferAccept, Inq, KeepIdle, MaxSegment, NoDelay, SynCnt, UserTimeout,
-        WindowClamp,
+        Congestion, Cork, DeferAccept, Inq, KeepCnt, KeepIdle, KeepIntvl, MaxSegment, NoDelay,
+        SynCnt, UserTimeout, WindowClamp,
     },
     prelude::*,
     util::net::options::SocketOption,
@@ -28,7 +28,9 @@ pub enum CTcpOptionName {
     /// Start keeplives after this period     
     KEEPIDLE = 4,
     /// Interval between keepalives
-    KEEPALIVE = 5,
+    KEEPINTVL = 5,
+    /// Number of keepalives before death
+    KEEPCNT = 6,
     /// Number of SYN retransmits
     SYNCNT = 7,
     /// Wake up listener only when data arriv
@@ -48,7 +50,10 @@ pub fn new_tcp_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
     match name {
         CTcpOptionName::NODELAY => Ok(Box::new(NoDelay::new())),
         CTcpOptionName::MAXSEG => Ok(Box::new(MaxSegment::new())),
+        CTcpOptionName::CORK => Ok(Box::new(Cork::new())),
         CTcpOptionName::KEEPIDLE => Ok(Box::new(KeepIdle::new())),
+        CTcpOptionName::KEEPINTVL => Ok(Box::new(KeepIntvl::new())),
+        CTcpOptionName::KEEPCNT => Ok(Box::new(KeepCnt::new())),
         CTcpOptionName::SYNCNT => Ok(Box::new(SynCnt::new())),
         CTcpOptionName::DEFER_ACCEPT => Ok(Box::new(DeferAccept::new())),
         CTcpOptionName::WINDOW_CLAMP => Ok(Box::new(WindowClamp::new())),
@@ -61,7 +66,10 @@ pub fn new_tcp_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
 
 impl_raw_socket_option!(NoDelay);
 impl_raw_socket_option!(MaxSegment);
+impl_raw_socket_option!(Cork);
 impl_raw_socket_option!(KeepIdle);
+impl_raw_socket_option!(KeepIntvl);
+impl_raw_socket_option!(KeepCnt);
 impl_raw_socket_option!(SynCnt);
 impl_raw_socket_option!(DeferAccept);
 impl_raw_socket_option!(WindowClamp);
@@ -6,7 +6,9 @@ use crate::{
     current_userspace,
     net::socket::{
         ip::{options::IpTtl, stream::CongestionControl},
-        LingerOption,
+        packet::{CTpacketReq3, PacketStats, TpacketVersion},
+        unix::CUserCred,
+        CSockFilter, LingerOption, SocketFilter, TimestampingFlags,
     },
     prelude::*,
 };
@@ -162,6 +164,122 @@ impl WriteToUser for LingerOption {
     }
 }
 
+impl WriteToUser for CUserCred {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        // Like Linux, the credentials are truncated if the buffer is too short.
+        let write_len = core::mem::size_of::<CUserCred>().min(max_len as usize);
+
+        current_userspace!()
+            .write_bytes(addr, &mut VmReader::from(&self.as_bytes()[..write_len]))?;
+
+        Ok(write_len)
+    }
+}
+
+impl ReadFromUser for () {
+    fn read_from_user(_addr: Vaddr, _max_len: u32) -> Result<Self> {
+        // Like Linux, the option value is ignored.
+        Ok(())
+    }
+}
+
+impl ReadFromUser for SocketFilter {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        if (max_len as usize) < core::mem::size_of::<CSockFprog>() {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        }
+
+        let user_space = current_userspace!();
+
+        let c_fprog = user_space.read_val::<CSockFprog>(addr)?;
+        let nr_insns = c_fprog.len as usize;
+        if nr_insns == 0 || nr_insns > SocketFilter::MAX_NR_INSNS {
+            return_errno_with_message!(Errno::EINVAL, "the number of instructions is invalid");
+        }
+
+        let c_insns = (0..nr_insns)
+            .map(|i| {
+                let insn_addr = (c_fprog.filter as Vaddr)
+                    .checked_add(i * core::mem::size_of::<CSockFilter>())
+                    .ok_or_else(|| Error::with_message(Errno::EFAULT, "the address overflows"))?;
+                user_space.read_val::<CSockFilter>(insn_addr)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        SocketFilter::new(&c_insns)
+    }
+}
+
+impl ReadFromUser for TimestampingFlags {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        // The value can also be `struct so_timestamping`, whose first field is the flags. The
+        // second field is only used with `SOF_TIMESTAMPING_BIND_PHC`, which is not supported.
+        let val = u32::read_from_user(addr, max_len)?;
+        TimestampingFlags::from_bits(val)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid timestamping flags"))
+    }
+}
+
+impl WriteToUser for TimestampingFlags {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        let so_timestamping = CSoTimestamping {
+            flags: self.bits() as i32,
+            bind_phc: 0,
+        };
+
+        // Like Linux, the value is truncated if the buffer is too short.
+        let write_len = core::mem::size_of::<CSoTimestamping>().min(max_len as usize);
+
+        current_userspace!().write_bytes(
+            addr,
+            &mut VmReader::from(&so_timestamping.as_bytes()[..write_len]),
+        )?;
+
+        Ok(write_len)
+    }
+}
+
+impl ReadFromUser for TpacketVersion {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        let val = i32::read_from_user(addr, max_len)?;
+        TpacketVersion::try_from(val)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid TPACKET version"))
+    }
+}
+
+impl WriteToUser for TpacketVersion {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        (*self as i32).write_to_user(addr, max_len)
+    }
+}
+
+impl ReadFromUser for CTpacketReq3 {
+    fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
+        if (max_len as usize) < core::mem::size_of::<CTpacketReq3>() {
+            return_errno_with_message!(Errno::EINVAL, "max_len is too short");
+        }
+
+        current_userspace!().read_val::<CTpacketReq3>(addr)
+    }
+}
+
+impl WriteToUser for PacketStats {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        let words = [self.packets, self.drops, self.freeze_q_cnt.unwrap_or(0)];
+        let bytes = match self.freeze_q_cnt {
+            Some(_) => words.as_bytes(),
+            None => &words.as_bytes()[..core::mem::size_of::<u32>() * 2],
+        };
+
+        // Like Linux, the statistics are truncated if the buffer is too short.
+        let write_len = bytes.len().min(max_len as usize);
+
+        current_userspace!().write_bytes(addr, &mut VmReader::from(&bytes[..write_len]))?;
+
+        Ok(write_len)
+    }
+}
+
 const TCP_CONGESTION_NAME_MAX: u32 = 16;
 
 impl ReadFromUser for CongestionControl {
@@ -225,3 +343,24 @@ impl From<CLinger> for LingerOption {
         LingerOption::new(is_on, timeout)
     }
 }
+
+/// A classic BPF program in the user space.
+///
+/// This is `struct sock_fprog` in Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CSockFprog {
+    len: u16,
+    _pad: [u8; 6],
+    filter: u64,
+}
+
+/// The value of `SO_TIMESTAMPING`.
+///
+/// This is `struct so_timestamping` in Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CSoTimestamping {
+    flags: i32,
+    bind_phc: i32,
+}
@@ -2,7 +2,7 @@
 
 use super::read_socket_addr_from_user;
 use crate::{
-    net::socket::SocketAddr,
+    net::socket::{ControlMessage, SendRecvFlags, SocketAddr},
     prelude::*,
     util::{net::write_socket_addr_with_max_len, VmReaderArray, VmWriterArray},
 };
@@ -28,6 +28,7 @@ pub enum Protocol {
     IPPROTO_GRE = 47,       /* Cisco GRE tunnels (rfc 1701,1702)	*/
     IPPROTO_ESP = 50,       /* Encapsulation Security Payload protocol */
     IPPROTO_AH = 51,        /* Authentication Header protocol	*/
+    IPPROTO_ICMPV6 = 58,    /* ICMPv6				*/
     IPPROTO_MTP = 92,       /* Multicast Transport Protocol		*/
     IPPROTO_BEETPH = 94,    /* IP option pseudo header for BEET	*/
     IPPROTO_ENCAP = 98,     /* Encapsulation Header			*/
@@ -84,15 +85,25 @@ pub struct CUserMsgHdr {
     /// Scatter/Gather iov array
     pub msg_iov: Vaddr,
     /// The # of elements in msg_iov
-    pub msg_iovlen: u32,
+    pub msg_iovlen: usize,
     /// Ancillary data
     pub msg_control: Vaddr,
     /// Ancillary data buffer length
-    pub msg_controllen: u32,
+    pub msg_controllen: usize,
     /// Flags on received message
     pub msg_flags: u32,
 }
 
+/// A message header used by `sendmmsg` and `recvmmsg`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CUserMMsgHdr {
+    /// The message header
+    pub msg_hdr: CUserMsgHdr,
+    /// The # of bytes transmitted
+    pub msg_len: u32,
+}
+
 impl CUserMsgHdr {
     pub fn read_socket_addr_from_user(&self) -> Result<Option<SocketAddr>> {
         if self.msg_name == 0 {
@@ -112,17 +123,54 @@ impl CUserMsgHdr {
         Ok(())
     }
 
+    pub fn read_control_messages_from_user(&self, ctx: &Context) -> Result<Vec<ControlMessage>> {
+        if self.msg_control == 0 {
+            return Ok(Vec::new());
+        }
+
+        if self.msg_controllen > i32::MAX as usize {
+            return_errno_with_message!(Errno::ENOBUFS, "the control message buffer is too large");
+        }
+
+        let user_space = ctx.user_space();
+        let mut reader = user_space.reader(self.msg_control, self.msg_controllen)?;
+        ControlMessage::read_all_from(&mut reader, ctx)
+    }
+
+    /// Writes the control messages to the user space.
+    ///
+    /// Returns the number of bytes written and whether the control messages are truncated.
+    pub fn write_control_messages_to_user(
+        &self,
+        control_messages: Vec<ControlMessage>,
+        flags: SendRecvFlags,
+        ctx: &Context,
+    ) -> Result<(usize, bool)> {
+        if control_messages.is_empty() {
+            return Ok((0, false));
+        }
+        if self.msg_control == 0 {
+            return Ok((0, true));
+        }
+
+        let user_space = ctx.user_space();
+        let mut writer = user_space.writer(self.msg_control, self.msg_controllen)?;
+        let is_truncated = ControlMessage::write_all_to(control_messages, &mut writer, flags, ctx)?;
+
+        Ok((self.msg_controllen - writer.avail(), is_truncated))
+    }
+
     pub fn copy_reader_array_from_user<'a>(
         &self,
         user_space: &'a CurrentUserSpace<'a>,
     ) -> Result<VmReaderArray<'a>> {
-        VmReaderArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen as usize)
+        VmReaderArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen)
     }
 
     pub fn copy_writer_array_from_user<'a>(
         &self,
         user_space: &'a CurrentUserSpace<'a>,
     ) -> Result<VmWriterArray<'a>> {
-        VmWriterArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen as usize)
+        VmWriterArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen)
     }
 }
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The cryptographically secure pseudorandom number generator (CRNG).
+//!
+//! The CRNG generates random bytes with ChaCha20 and erases its key on every
+//! use ("fast key erasure"). Each request takes a block from the base key: the
+//! first half replaces the base key, and the second half keys a ChaCha20 stream
+//! that generates the requested bytes without holding any lock. Since the base
+//! key only moves forward, a compromised CRNG reveals nothing about the bytes
+//! generated before.
+//!
+//! Reference: <https://blog.cr.yp.to/20170723-random.html>
+
+use core::time::Duration;
+
+use ostd::{sync::LocalIrqDisabled, timer::Jiffies};
+
+use super::pool::SEED_SIZE;
+use crate::{
+    prelude::*,
+    util::crypto::{
+        blake2s::Blake2s,
+        chacha::{chacha20_block, BLOCK_SIZE, KEY_SIZE},
+    },
+};
+
+/// The interval to reseed the CRNG from the input pool.
+const RESEED_INTERVAL: Duration = Duration::from_secs(60);
+
+struct BaseCrng {
+    key: [u8; KEY_SIZE],
+    /// The time of the last reseed.
+    last_reseed: Duration,
+}
+
+static BASE_CRNG: SpinLock<BaseCrng, LocalIrqDisabled> = SpinLock::new(BaseCrng {
+    key: [0; KEY_SIZE],
+    last_reseed: Duration::ZERO,
+});
+
+/// Reseeds the CRNG with `seed`.
+pub(super) fn reseed(seed: &[u8; SEED_SIZE]) {
+    let mut crng = BASE_CRNG.lock();
+
+    // The old key is kept in the mix, so a weak seed cannot weaken the CRNG.
+    let mut hasher = Blake2s::new();
+    hasher.update(&crng.key);
+    hasher.update(seed);
+    crng.key = hasher.finalize();
+    crng.last_reseed = Jiffies::elapsed().as_duration();
+}
+
+/// Returns whether it is time to reseed the CRNG.
+pub(super) fn is_reseed_due() -> bool {
+    let last_reseed = BASE_CRNG.lock().last_reseed;
+    Jiffies::elapsed().as_duration() >= last_reseed + RESEED_INTERVAL
+}
+
+/// Fills `dst` with random bytes.
+pub(super) fn fill(dst: &mut [u8]) {
+    let mut key = [0; KEY_SIZE];
+    {
+        let mut crng = BASE_CRNG.lock();
+        let block = chacha20_block(&crng.key, 0, 0);
+        crng.key.copy_from_slice(&block[..KEY_SIZE]);
+        key.copy_from_slice(&block[KEY_SIZE..]);
+    }
+
+    for (counter, chunk) in dst.chunks_mut(BLOCK_SIZE).enumerate() {
+        let block = chacha20_block(&key, counter as u64, 0);
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+
+    // SAFETY: `key` is a valid local variable. The volatile write keeps the
+    // compiler from eliding the erasure of the dead key.
+    unsafe { core::ptr::write_volatile(&mut key, [0; KEY_SIZE]) };
+}
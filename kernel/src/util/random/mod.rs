@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The kernel random number generator.
+//!
+//! Entropy is collected into the input pool (see [`pool`]) from
+//!  - the hardware random number generator (e.g., `RDSEED` and `RDRAND`) and the
+//!    seed passed by the bootloader, which are trusted at boot;
+//!  - the arrival times of interrupts;
+//!  - the jitter of the execution time of the CPU.
+//!
+//! Random bytes are generated by the CRNG (see [`crng`]), which is reseeded from
+//! the input pool periodically. The CRNG is ready once [`READY_BITS`] bits of
+//! entropy have been credited to the input pool. Before that, the random bytes
+//! are only as unpredictable as the inputs collected so far.
+
+mod crng;
+mod pool;
+
+use core::{
+    cell::RefCell,
+    hint::black_box,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    arch::{read_random, read_random_seed, read_tsc},
+    cpu_local,
+    sync::WaitQueue,
+    timer::Jiffies,
+    trap::{disable_local, register_irq_sampler},
+};
+
+use self::pool::FastPool;
+use crate::{prelude::*, process::signal::Pause, util::crypto::blake2s::Blake2s};
+
+/// The bits of entropy needed before the CRNG is ready.
+const READY_BITS: usize = 256;
+
+/// The number of samples from interrupts or jitter to credit a bit of entropy.
+const SAMPLES_PER_BIT: usize = 64;
+
+/// The minimum interval between the dumps of a fast pool after the CRNG is ready.
+const FAST_POOL_DUMP_INTERVAL: Duration = Duration::from_secs(1);
+
+static IS_READY: AtomicBool = AtomicBool::new(false);
+
+static READY_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+cpu_local! {
+    static FAST_POOLS: RefCell<FastPool> = RefCell::new(FastPool::new());
+}
+
+/// Fills `dst` with random bytes.
+///
+/// This method never blocks. The random bytes are cryptographically secure
+/// once the CRNG is ready (see [`is_ready`]).
+pub fn getrandom(dst: &mut [u8]) {
+    if is_ready() && crng::is_reseed_due() {
+        crng::reseed(&pool::extract());
+    }
+    crng::fill(dst);
+}
+
+/// Fills `writer` with random bytes like [`getrandom`].
+///
+/// Returns the number of bytes written. If the writer fails after some bytes
+/// have been written, the number of the written bytes is returned instead of
+/// the error.
+pub fn fill_writer(writer: &mut VmWriter) -> Result<usize> {
+    let mut buf = [0u8; 512];
+    let mut written_len = 0;
+
+    while writer.has_avail() {
+        let len = writer.avail().min(buf.len());
+        getrandom(&mut buf[..len]);
+        match writer.write_fallible(&mut VmReader::from(&buf[..len])) {
+            Ok(len) => written_len += len,
+            Err((err, len)) => {
+                written_len += len;
+                if written_len == 0 {
+                    return Err(err.into());
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(written_len)
+}
+
+/// Returns whether the CRNG is ready.
+pub fn is_ready() -> bool {
+    IS_READY.load(Ordering::Acquire)
+}
+
+/// Waits until the CRNG is ready.
+///
+/// While waiting, entropy is generated from the jitter of the execution time
+/// of the CPU, so the wait is usually short.
+///
+/// # Errors
+///
+/// This method will return an error with [`EINTR`] if a signal is received
+/// before the CRNG is ready.
+///
+/// [`EINTR`]: crate::error::Errno::EINTR
+pub fn wait_until_ready() -> Result<()> {
+    READY_WAIT_QUEUE.pause_until(|| {
+        if !is_ready() {
+            add_jitter_entropy();
+        }
+        is_ready().then_some(())
+    })
+}
+
+/// Mixes `data` into the input pool without crediting any entropy.
+///
+/// This is useful for data that is unique to the system or supplied by the
+/// user, which makes the random bytes harder to predict but may be known to
+/// an attacker.
+pub fn add_device_randomness(data: &[u8]) {
+    pool::mix(data);
+}
+
+pub fn init() {
+    let mut seed = Vec::new();
+    let mut seed_bits = 0;
+
+    // The hardware random number generator is trusted. `RDRAND` is only used
+    // if the hardware entropy source is unavailable.
+    for _ in 0..(READY_BITS / u64::BITS as usize) {
+        if let Some(value) = read_random_seed().or_else(read_random) {
+            seed.extend_from_slice(&value.to_ne_bytes());
+            seed_bits += u64::BITS as usize;
+        }
+    }
+
+    // The seed passed by the bootloader is trusted.
+    #[cfg(target_arch = "riscv64")]
+    if let Some(rng_seed) = ostd::arch::boot::DEVICE_TREE
+        .get()
+        .and_then(|device_tree| device_tree.find_node("/chosen"))
+        .and_then(|chosen| chosen.property("rng-seed"))
+    {
+        seed.extend_from_slice(rng_seed.value);
+        seed_bits += rng_seed.value.len() * u8::BITS as usize;
+    }
+
+    // The time of boot is not trusted, but it does no harm.
+    seed.extend_from_slice(&read_tsc().to_ne_bytes());
+    let entropy_bits = pool::mix_and_credit(&seed, seed_bits);
+
+    // Seed the CRNG with the best we have, even if it is not ready.
+    let mut hasher = Blake2s::new();
+    hasher.update(&seed);
+    crng::reseed(&hasher.finalize());
+    credit_entropy(entropy_bits);
+    if !is_ready() {
+        warn!("the random number generator is not ready due to insufficient entropy");
+    }
+
+    register_irq_sampler(sample_irq);
+}
+
+/// Marks the CRNG as ready if enough entropy has been credited.
+fn credit_entropy(entropy_bits: usize) {
+    if is_ready() || entropy_bits < READY_BITS {
+        return;
+    }
+
+    crng::reseed(&pool::extract());
+    if !IS_READY.swap(true, Ordering::AcqRel) {
+        READY_WAIT_QUEUE.wake_all();
+    }
+}
+
+fn sample_irq(irq_num: usize) {
+    let irq_guard = disable_local();
+    let mut fast_pool = FAST_POOLS.get_with(&irq_guard).borrow_mut();
+
+    let now = Jiffies::elapsed().as_duration();
+    fast_pool.mix(read_tsc() ^ irq_num as u64, now.as_nanos() as u64);
+
+    // Before the CRNG is ready, a fast pool is dumped as soon as it can be
+    // credited. Afterwards, it is dumped at most once per interval to keep the
+    // input pool uncontended.
+    if fast_pool.count() < SAMPLES_PER_BIT
+        || (is_ready() && now < fast_pool.last_dump() + FAST_POOL_DUMP_INTERVAL)
+    {
+        return;
+    }
+    let entropy_bits = fast_pool.dump(1, now);
+    drop(fast_pool);
+
+    credit_entropy(entropy_bits);
+}
+
+/// Generates entropy from the jitter of the execution time of the CPU.
+///
+/// The time to execute a few memory accesses varies with the states of the
+/// caches, the pipelines, and the interrupts, which are hard to predict. Only
+/// the samples whose time differs from the previous one are counted.
+fn add_jitter_entropy() {
+    const NR_SAMPLES: usize = READY_BITS * SAMPLES_PER_BIT;
+
+    let mut fast_pool = FastPool::new();
+    let mut scratch = [0u64; 64];
+    let mut last_delta = 0;
+
+    for i in 0..NR_SAMPLES {
+        let start = read_tsc();
+        for (j, word) in scratch.iter_mut().enumerate() {
+            *word = word.rotate_left(7) ^ start.wrapping_add(j as u64);
+        }
+        black_box(&mut scratch);
+        let delta = read_tsc().wrapping_sub(start);
+
+        if delta == last_delta {
+            continue;
+        }
+        last_delta = delta;
+
+        fast_pool.mix(delta, scratch[i % scratch.len()]);
+        if fast_pool.count() >= SAMPLES_PER_BIT {
+            let entropy_bits = fast_pool.dump(1, Jiffies::elapsed().as_duration());
+            credit_entropy(entropy_bits);
+            if is_ready() {
+                break;
+            }
+        }
+    }
+}
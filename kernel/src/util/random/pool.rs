@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The entropy pools.
+//!
+//! Entropy is accumulated in the input pool, which hashes everything mixed into
+//! it with BLAKE2s. Extracting a seed finalizes the hash and starts a new one,
+//! so a seed depends on all the inputs ever mixed into the pool.
+//!
+//! Interrupts are too frequent to hash each of their samples into the input
+//! pool, so the samples are collected in per-CPU fast pools first, which are
+//! dumped into the input pool from time to time (see [`FastPool`]).
+
+use core::time::Duration;
+
+use ostd::sync::LocalIrqDisabled;
+
+use crate::{
+    prelude::*,
+    util::crypto::blake2s::{Blake2s, DIGEST_SIZE},
+};
+
+/// The size of a seed extracted from the input pool.
+pub(super) const SEED_SIZE: usize = DIGEST_SIZE;
+
+struct InputPool {
+    hasher: Blake2s,
+    /// The bits of entropy credited since the last extraction.
+    entropy_bits: usize,
+}
+
+static INPUT_POOL: SpinLock<InputPool, LocalIrqDisabled> = SpinLock::new(InputPool {
+    hasher: Blake2s::new(),
+    entropy_bits: 0,
+});
+
+/// Mixes `data` into the input pool without crediting any entropy.
+pub(super) fn mix(data: &[u8]) {
+    INPUT_POOL.lock().hasher.update(data);
+}
+
+/// Mixes `data` into the input pool and credits `bits` bits of entropy.
+///
+/// Returns the bits of entropy credited since the last extraction.
+pub(super) fn mix_and_credit(data: &[u8], bits: usize) -> usize {
+    let mut pool = INPUT_POOL.lock();
+    pool.hasher.update(data);
+    pool.entropy_bits = pool.entropy_bits.saturating_add(bits);
+    pool.entropy_bits
+}
+
+/// Extracts a seed from the input pool.
+///
+/// The credited entropy is consumed by the extraction.
+pub(super) fn extract() -> [u8; SEED_SIZE] {
+    let mut pool = INPUT_POOL.lock();
+    let digest = core::mem::replace(&mut pool.hasher, Blake2s::new()).finalize();
+    pool.entropy_bits = 0;
+
+    // The next hash starts with a value derived from the digest. It cannot be
+    // computed from the returned seed, so the seed reveals nothing about later
+    // seeds.
+    pool.hasher.update(&derive(&digest, 0));
+    derive(&digest, 1)
+}
+
+fn derive(digest: &[u8; DIGEST_SIZE], label: u8) -> [u8; DIGEST_SIZE] {
+    let mut hasher = Blake2s::new();
+    hasher.update(digest);
+    hasher.update(&[label]);
+    hasher.finalize()
+}
+
+/// A pool that collects samples cheaply.
+///
+/// Samples are mixed with SipHash rounds, which are much cheaper than hashing
+/// them into the input pool.
+pub(super) struct FastPool {
+    state: [u64; 4],
+    /// The number of samples since the last dump.
+    count: usize,
+    /// The time of the last dump.
+    last_dump: Duration,
+}
+
+impl FastPool {
+    pub(super) const fn new() -> Self {
+        Self {
+            // The initialization constants of SipHash.
+            state: [
+                0x736f_6d65_7073_6575,
+                0x646f_7261_6e64_6f6d,
+                0x6c79_6765_6e65_7261,
+                0x7465_6462_7974_6573,
+            ],
+            count: 0,
+            last_dump: Duration::ZERO,
+        }
+    }
+
+    /// Mixes a sample of two words into the pool.
+    pub(super) fn mix(&mut self, v1: u64, v2: u64) {
+        self.state[3] ^= v1;
+        self.sip_round();
+        self.state[0] ^= v1;
+        self.state[3] ^= v2;
+        self.sip_round();
+        self.state[0] ^= v2;
+
+        self.count += 1;
+    }
+
+    /// Returns the number of samples since the last dump.
+    pub(super) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the time of the last dump.
+    pub(super) fn last_dump(&self) -> Duration {
+        self.last_dump
+    }
+
+    /// Dumps the pool into the input pool and credits `bits` bits of entropy.
+    ///
+    /// Returns the bits of entropy credited since the last extraction.
+    pub(super) fn dump(&mut self, bits: usize, now: Duration) -> usize {
+        let mut data = [0u8; 32];
+        for (chunk, word) in data.chunks_exact_mut(8).zip(self.state) {
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+        self.count = 0;
+        self.last_dump = now;
+
+        mix_and_credit(&data, bits)
+    }
+
+    fn sip_round(&mut self) {
+        let [v0, v1, v2, v3] = &mut self.state;
+        *v0 = v0.wrapping_add(*v1);
+        *v1 = v1.rotate_left(13) ^ *v0;
+        *v0 = v0.rotate_left(32);
+        *v2 = v2.wrapping_add(*v3);
+        *v3 = v3.rotate_left(16) ^ *v2;
+        *v0 = v0.wrapping_add(*v3);
+        *v3 = v3.rotate_left(21) ^ *v0;
+        *v2 = v2.wrapping_add(*v1);
+        *v1 = v1.rotate_left(17) ^ *v2;
+        *v2 = v2.rotate_left(32);
+    }
+}
@@ -335,6 +335,42 @@ impl<R: Deref<Target = RingBuffer<u8>>> Producer<u8, R> {
         rb.advance_tail(tail, write_len);
         Ok(write_len)
     }
+
+    /// Writes at most `max_len` bytes to the `RingBuffer` with `write_fn`.
+    ///
+    /// `write_fn` is called with writers to the free space of the ring buffer, and returns the
+    /// number of bytes written. This allows data to be written to the ring buffer directly
+    /// (e.g., from the page cache) without an intermediate buffer.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_with(
+        &mut self,
+        max_len: usize,
+        write_fn: &mut dyn FnMut(&mut VmWriter) -> Result<usize>,
+    ) -> Result<usize> {
+        let rb = &self.rb;
+        let write_len = max_len.min(rb.free_len());
+        if write_len == 0 {
+            return Ok(0);
+        }
+
+        let tail = rb.tail();
+        let first_len = write_len.min(rb.capacity - tail);
+        let mut writer = rb.segment.writer();
+        writer.skip(tail).limit(first_len);
+        let mut len = write_fn(&mut writer.to_fallible())?;
+
+        if len == first_len && first_len < write_len {
+            // Write into the second part. Since some bytes have been written, a failure here is
+            // reported as a short write.
+            let mut writer = rb.segment.writer();
+            writer.limit(write_len - first_len);
+            len += write_fn(&mut writer.to_fallible()).unwrap_or(0);
+        }
+
+        rb.advance_tail(tail, len);
+        Ok(len)
+    }
 }
 
 #[inherit_methods(from = "self.rb")]
@@ -435,6 +471,57 @@ impl<R: Deref<Target = RingBuffer<u8>>> Consumer<u8, R> {
         rb.advance_head(head, read_len);
         Ok(read_len)
     }
+
+    /// Reads at most `max_len` bytes from the `RingBuffer` with `read_fn`.
+    ///
+    /// `read_fn` is called with readers of the data in the ring buffer, and returns the number of
+    /// bytes read. This allows data to be read from the ring buffer directly (e.g., to the page
+    /// cache) without an intermediate buffer.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read_with(
+        &mut self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        let read_len = self.peek_with(max_len, read_fn)?;
+        let head = self.rb.head();
+        self.rb.advance_head(head, read_len);
+        Ok(read_len)
+    }
+
+    /// Reads at most `max_len` bytes from the `RingBuffer` with `read_fn`, without consuming them.
+    ///
+    /// See [`Self::read_with`] for the usage of `read_fn`.
+    ///
+    /// Returns the number of bytes read.
+    pub fn peek_with(
+        &self,
+        max_len: usize,
+        read_fn: &mut dyn FnMut(&mut VmReader) -> Result<usize>,
+    ) -> Result<usize> {
+        let rb = &self.rb;
+        let read_len = max_len.min(rb.len());
+        if read_len == 0 {
+            return Ok(0);
+        }
+
+        let head = rb.head();
+        let first_len = read_len.min(rb.capacity - head);
+        let mut reader = rb.segment.reader();
+        reader.skip(head).limit(first_len);
+        let mut len = read_fn(&mut reader.to_fallible())?;
+
+        if len == first_len && first_len < read_len {
+            // Read from the second part. Since some bytes have been read, a failure here is
+            // reported as a short read.
+            let mut reader = rb.segment.reader();
+            reader.limit(read_len - first_len);
+            len += read_fn(&mut reader.to_fallible()).unwrap_or(0);
+        }
+
+        Ok(len)
+    }
 }
 
 #[inherit_methods(from = "self.rb")]
@@ -11,34 +11,38 @@
 //! necessary time-related information, and a Virtual Memory Object (VMO) that encapsulates both the data and the
 //! VDSO routines. The VMO is intended to be mapped into the address space of every user space process for efficient access.
 //!
-//! The module is initialized with `init`, which sets up the `START_SECS_COUNT` and prepares the VDSO instance for
-//! use. It also hooks up the VDSO data update routine to the time management subsystem for periodic updates.
+//! The module is initialized with `init`, which prepares the VDSO instance for use. It also hooks up the VDSO data update routine to the time management subsystem for periodic updates.
+//!
+//! Besides the time-related information shared by all processes, each process has a `VdsoIdentity` page mapped
+//! next to the VDSO data, which publishes the process ID and the user/group IDs. The user space can answer
+//! `getpid`, `getuid` and the like by reading the page instead of issuing system calls.
 
-use alloc::{boxed::Box, sync::Arc};
 use core::{mem::ManuallyDrop, time::Duration};
 
-use aster_rights::Rights;
-use aster_time::{read_monotonic_time, Instant};
+use align_ext::AlignExt;
+use aster_rights::{Full, ReadOp, Rights};
+use aster_time::{read_monotonic_time, ClockSource, Instant};
 use aster_util::coeff::Coeff;
-use ostd::{
-    mm::{UFrame, VmIo, PAGE_SIZE},
-    sync::SpinLock,
-    Pod,
-};
+use ostd::mm::{UFrame, VmIo};
 use spin::Once;
 
 use crate::{
     fs::fs_resolver::{FsPath, FsResolver, AT_FDCWD},
+    prelude::*,
+    process::{Credentials, Pid},
     syscall::ClockId,
-    time::{clocks::MonotonicClock, timer::Timeout, SystemTime, START_TIME},
-    vm::vmo::{Vmo, VmoOptions},
+    time::{clocks::MonotonicClock, realtime_at, remaining_slew, timer::Timeout},
+    vm::{
+        perms::VmPerms,
+        vmar::Vmar,
+        vmo::{CommitFlags, Vmo, VmoOptions, VmoRightsOp},
+    },
 };
 
 const CLOCK_TAI: usize = 11;
 const VDSO_BASES: usize = CLOCK_TAI + 1;
 const DEFAULT_CLOCK_MODE: VdsoClockMode = VdsoClockMode::Tsc;
 
-static START_SECS_COUNT: Once<u64> = Once::new();
 static VDSO: Once<Arc<Vdso>> = Once::new();
 
 #[derive(Debug, Copy, Clone)]
@@ -136,15 +140,20 @@ impl VdsoData {
     /// Init VDSO data based on the default clocksource.
     fn init(&mut self) {
         let clocksource = aster_time::default_clocksource();
-        let coeff = clocksource.coeff();
-        self.set_clock_mode(DEFAULT_CLOCK_MODE);
-        self.set_coeff(coeff);
+        self.set_clocksource(&clocksource);
+        // The resolution (in nanoseconds) reported by `clock_getres` for high-resolution clocks.
+        self.hrtimer_res = 1;
 
         let (last_instant, last_cycles) = clocksource.last_record();
         self.update_high_res_instant(last_instant, last_cycles);
         self.update_coarse_res_instant(last_instant);
     }
 
+    fn set_clocksource(&mut self, clocksource: &ClockSource) {
+        self.set_clock_mode(clock_mode_of(clocksource));
+        self.set_coeff(clocksource.coeff());
+    }
+
     fn set_clock_mode(&mut self, mode: VdsoClockMode) {
         self.clock_mode = mode as i32;
     }
@@ -162,32 +171,45 @@ impl VdsoData {
     fn update_high_res_instant(&mut self, instant: Instant, instant_cycles: u64) {
         self.last_cycles = instant_cycles;
         for clock_id in HIGH_RES_CLOCK_IDS {
-            let secs = if clock_id == ClockId::CLOCK_REALTIME {
-                instant.secs() + START_SECS_COUNT.get().unwrap()
+            let (secs, nanos) = if clock_id == ClockId::CLOCK_REALTIME {
+                realtime_secs_and_nanos(instant)
             } else {
-                instant.secs()
+                (instant.secs(), instant.nanos())
             };
 
-            self.update_clock_instant(
-                clock_id as usize,
-                secs,
-                (instant.nanos() as u64) << self.shift as u64,
-            );
+            self.update_clock_instant(clock_id as usize, secs, (nanos as u64) << self.shift as u64);
         }
     }
 
     fn update_coarse_res_instant(&mut self, instant: Instant) {
         for clock_id in COARSE_RES_CLOCK_IDS {
-            let secs = if clock_id == ClockId::CLOCK_REALTIME_COARSE {
-                instant.secs() + START_SECS_COUNT.get().unwrap()
+            let (secs, nanos) = if clock_id == ClockId::CLOCK_REALTIME_COARSE {
+                realtime_secs_and_nanos(instant)
             } else {
-                instant.secs()
+                (instant.secs(), instant.nanos())
             };
-            self.update_clock_instant(clock_id as usize, secs, instant.nanos() as u64);
+            self.update_clock_instant(clock_id as usize, secs, nanos as u64);
         }
     }
 }
 
+/// Returns the clock mode with which the user space reads the time of the clocksource.
+fn clock_mode_of(clocksource: &ClockSource) -> VdsoClockMode {
+    // The user space can only read the TSC. It cannot follow the real time while the real time is
+    // slewed, either. Otherwise, it falls back to system calls.
+    if clocksource.name() == "tsc" && remaining_slew() == 0 {
+        DEFAULT_CLOCK_MODE
+    } else {
+        VdsoClockMode::None
+    }
+}
+
+/// Returns the seconds and the nanoseconds of the real time at the instant.
+fn realtime_secs_and_nanos(instant: Instant) -> (u64, u32) {
+    let realtime = realtime_at(Duration::new(instant.secs(), instant.nanos()));
+    (realtime.as_secs(), realtime.subsec_nanos())
+}
+
 /// Vdso (virtual dynamic shared object) is used to export some safe kernel space routines to user space applications
 /// so that applications can call these kernel space routines in-process, without context switching.
 ///
@@ -207,40 +229,38 @@ struct Vdso {
 /// A `SpinLock` for the `seq` field in `VdsoData`.
 static SEQ_LOCK: SpinLock<()> = SpinLock::new(());
 
-/// The size of the VDSO VMO.
-pub const VDSO_VMO_SIZE: usize = 5 * PAGE_SIZE;
+/// The path of the VDSO library.
+const VDSO_LIB_PATH: &str = "/lib/x86_64-linux-gnu/vdso64.so";
+
+/// The offset of the VDSO library text from the start of the VDSO VMO.
+///
+/// The VDSO library of Linux expects its data pages (i.e., the `vvar` pages) to
+/// be right before its text.
+pub const VDSO_TEXT_OFFSET: usize = 4 * PAGE_SIZE;
 
 impl Vdso {
     /// Construct a new `Vdso`, including an initialized `VdsoData` and a VMO of the VDSO.
-    fn new() -> Self {
+    ///
+    /// This fails if the VDSO library cannot be loaded.
+    fn new() -> Result<Self> {
         let mut vdso_data = VdsoData::empty();
         vdso_data.init();
 
-        let (vdso_vmo, data_frame) = {
-            let vmo_options = VmoOptions::<Rights>::new(VDSO_VMO_SIZE);
-            let vdso_vmo = vmo_options.alloc().unwrap();
-            // Write VDSO data to VDSO VMO.
-            vdso_vmo.write_bytes(0x80, vdso_data.as_bytes()).unwrap();
-
-            let vdso_lib_vmo = {
-                let vdso_path = FsPath::new(AT_FDCWD, "/lib/x86_64-linux-gnu/vdso64.so").unwrap();
-                let fs_resolver = FsResolver::new();
-                let vdso_lib = fs_resolver.lookup(&vdso_path).unwrap();
-                vdso_lib.inode().page_cache().unwrap()
-            };
-            let mut vdso_text = Box::new([0u8; PAGE_SIZE]);
-            vdso_lib_vmo.read_bytes(0, &mut *vdso_text).unwrap();
-            // Write VDSO library to VDSO VMO.
-            vdso_vmo.write_bytes(0x4000, &*vdso_text).unwrap();
+        let vdso_text = read_vdso_lib()?;
+        let vmo_size = VDSO_TEXT_OFFSET + vdso_text.len().align_up(PAGE_SIZE);
 
-            let data_frame = vdso_vmo.try_commit_page(0).unwrap();
-            (vdso_vmo, data_frame)
-        };
-        Self {
+        let vdso_vmo = VmoOptions::<Rights>::new(vmo_size).alloc()?;
+        // Write VDSO data to VDSO VMO.
+        vdso_vmo.write_bytes(0x80, vdso_data.as_bytes())?;
+        // Write VDSO library to VDSO VMO.
+        vdso_vmo.write_bytes(VDSO_TEXT_OFFSET, &vdso_text)?;
+        let data_frame = vdso_vmo.commit_on(0, CommitFlags::empty())?;
+
+        Ok(Self {
             data: SpinLock::new(vdso_data),
             vmo: Arc::new(vdso_vmo),
             data_frame,
-        }
+        })
     }
 
     fn update_high_res_instant(&self, instant: Instant, instant_cycles: u64) {
@@ -260,6 +280,51 @@ impl Vdso {
         self.data_frame.write_val(0x80, &0).unwrap();
     }
 
+    fn switch_clocksource(&self, clocksource: &ClockSource) {
+        let seq_lock = SEQ_LOCK.lock();
+        let (last_instant, last_cycles) = clocksource.last_record();
+        let (clock_mode, mult, shift) = {
+            let mut data = self.data.lock();
+            data.set_clocksource(clocksource);
+            data.update_high_res_instant(last_instant, last_cycles);
+            (data.clock_mode, data.mult, data.shift)
+        };
+
+        // Update begins.
+        self.data_frame.write_val(0x80, &1).unwrap();
+        self.data_frame.write_val(0x84, &clock_mode).unwrap();
+        self.data_frame.write_val(0x88, &last_cycles).unwrap();
+        self.data_frame.write_val(0x98, &mult).unwrap();
+        self.data_frame.write_val(0x9C, &shift).unwrap();
+        for clock_id in HIGH_RES_CLOCK_IDS {
+            self.update_data_frame_instant(clock_id);
+        }
+
+        // Update finishes.
+        self.data_frame.write_val(0x80, &0).unwrap();
+    }
+
+    /// Updates the clock mode if the real time starts or stops being slewed.
+    fn update_clock_mode(&self) {
+        let seq_lock = SEQ_LOCK.lock();
+        let clock_mode = {
+            let mut data = self.data.lock();
+            let clock_mode = clock_mode_of(&aster_time::default_clocksource()) as i32;
+            if data.clock_mode == clock_mode {
+                return;
+            }
+            data.clock_mode = clock_mode;
+            clock_mode
+        };
+
+        // Update begins.
+        self.data_frame.write_val(0x80, &1).unwrap();
+        self.data_frame.write_val(0x84, &clock_mode).unwrap();
+
+        // Update finishes.
+        self.data_frame.write_val(0x80, &0).unwrap();
+    }
+
     fn update_coarse_res_instant(&self, instant: Instant) {
         let seq_lock = SEQ_LOCK.lock();
         self.data.lock().update_coarse_res_instant(instant);
@@ -289,6 +354,33 @@ impl Vdso {
     }
 }
 
+/// Reads the entire image of the VDSO library.
+fn read_vdso_lib() -> Result<Vec<u8>> {
+    let vdso_lib = {
+        let vdso_path = FsPath::new(AT_FDCWD, VDSO_LIB_PATH)?;
+        FsResolver::new().lookup(&vdso_path)?
+    };
+    let len = vdso_lib.size();
+    if len == 0 || len > VDSO_LIB_MAX_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "the VDSO library has an invalid size");
+    }
+
+    let mut vdso_text = vec![0u8; len];
+    vdso_lib
+        .inode()
+        .page_cache()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the VDSO library is not a file"))?
+        .read_bytes(0, &mut vdso_text)?;
+    if !vdso_text.starts_with(b"\x7fELF") {
+        return_errno_with_message!(Errno::ENOEXEC, "the VDSO library is not an ELF file");
+    }
+
+    Ok(vdso_text)
+}
+
+/// The maximum size of the VDSO library.
+const VDSO_LIB_MAX_SIZE: usize = 16 * PAGE_SIZE;
+
 /// Update the `VdsoInstant` for clock IDs with high resolution in Vdso.
 fn update_vdso_high_res_instant(instant: Instant, instant_cycles: u64) {
     VDSO.get()
@@ -296,32 +388,47 @@ fn update_vdso_high_res_instant(instant: Instant, instant_cycles: u64) {
         .update_high_res_instant(instant, instant_cycles);
 }
 
+/// Update the clock mode and the coefficients in Vdso after the clocksource is switched.
+fn switch_vdso_clocksource(clocksource: &ClockSource) {
+    VDSO.get().unwrap().switch_clocksource(clocksource);
+}
+
 /// Update the `VdsoInstant` for clock IDs with coarse resolution in Vdso.
 fn update_vdso_coarse_res_instant() {
     let instant = Instant::from(read_monotonic_time());
-    VDSO.get().unwrap().update_coarse_res_instant(instant);
+    let vdso = VDSO.get().unwrap();
+    vdso.update_coarse_res_instant(instant);
+    // The slew of the real time stops without notice, so the clock mode is checked periodically.
+    vdso.update_clock_mode();
 }
 
-/// Init `START_SECS_COUNT`, which is used to record the seconds passed since 1970-01-01 00:00:00.
-fn init_start_secs_count() {
-    let time_duration = START_TIME
-        .get()
-        .unwrap()
-        .duration_since(&SystemTime::UNIX_EPOCH)
-        .unwrap();
-    START_SECS_COUNT.call_once(|| time_duration.as_secs());
+/// Updates the real time in Vdso after the real time is stepped or slewed.
+pub(crate) fn update_realtime() {
+    let Some(vdso) = VDSO.get() else {
+        return;
+    };
+    vdso.update_clock_mode();
+    let (last_instant, last_cycles) = aster_time::default_clocksource().last_record();
+    vdso.update_high_res_instant(last_instant, last_cycles);
+    vdso.update_coarse_res_instant(Instant::from(read_monotonic_time()));
 }
 
-fn init_vdso() {
-    let vdso = Vdso::new();
+fn init_vdso() -> Result<()> {
+    let vdso = Vdso::new()?;
     VDSO.call_once(|| Arc::new(vdso));
+    Ok(())
 }
 
 /// Init this module.
 pub(super) fn init() {
-    init_start_secs_count();
-    init_vdso();
+    if let Err(err) = init_vdso() {
+        // The user space can still run without the VDSO, but it will use system calls for the
+        // operations that the VDSO accelerates.
+        warn!("failed to load the VDSO library: {:?}", err);
+        return;
+    }
     aster_time::VDSO_DATA_HIGH_RES_UPDATE_FN.call_once(|| Arc::new(update_vdso_high_res_instant));
+    aster_time::VDSO_DATA_CLOCKSOURCE_SWITCH_FN.call_once(|| Arc::new(switch_vdso_clocksource));
 
     // Coarse resolution clock IDs directly read the instant stored in VDSO data without
     // using coefficients for calculation, thus the related instant requires more frequent updating.
@@ -337,3 +444,125 @@ pub(crate) fn vdso_vmo() -> Option<Arc<Vmo>> {
     // We allow that VDSO does not exist
     VDSO.get().map(|vdso| vdso.vmo.clone())
 }
+
+/// The offset of the identity page from the start of the VDSO mapping.
+///
+/// The page lies in the unused area between the VDSO data and the VDSO text.
+pub const VDSO_IDENTITY_OFFSET: usize = PAGE_SIZE;
+
+/// A POD structure publishing the identity of a process to the user space.
+///
+/// The user space should read `generation` before and after reading the other
+/// fields, and the read values are valid only if the two `generation`s are the
+/// same, even, and non-zero. Otherwise, it should fall back to system calls.
+///
+/// The user and group IDs are the credentials of the thread that changes them
+/// most recently, which are the credentials of all the threads if the threads
+/// change them in sync as required by POSIX.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+struct VdsoIdentityData {
+    generation: u64,
+    pid: u32,
+    uid: u32,
+    euid: u32,
+    gid: u32,
+    egid: u32,
+    __unused: u32,
+}
+
+/// The identity page of a process in the VDSO.
+///
+/// A `VdsoIdentity` is bound to an address space. Its `generation` is bumped
+/// whenever the identity changes, i.e., when the address space is forked to a
+/// new process, or when the process changes its user or group IDs.
+pub struct VdsoIdentity {
+    vmo: Vmo,
+    /// The `UFrame` that backs the identity page.
+    frame: UFrame,
+    inner: Mutex<VdsoIdentityInner>,
+}
+
+struct VdsoIdentityInner {
+    data: VdsoIdentityData,
+    /// The address where the page is mapped in the address space.
+    map_addr: Option<Vaddr>,
+    /// Whether the address space is shared by multiple processes.
+    ///
+    /// The page cannot describe more than one process, so it is kept invalid.
+    is_shared: bool,
+}
+
+impl VdsoIdentity {
+    /// Creates a new identity page, which is invalid until being updated.
+    pub fn new() -> Result<Self> {
+        let vmo = VmoOptions::<Rights>::new(PAGE_SIZE).alloc()?;
+        let frame = vmo.commit_on(0, CommitFlags::empty())?;
+        Ok(Self {
+            vmo,
+            frame,
+            inner: Mutex::new(VdsoIdentityInner {
+                data: VdsoIdentityData::default(),
+                map_addr: None,
+                is_shared: false,
+            }),
+        })
+    }
+
+    /// Maps the identity page to `addr` in `root_vmar`, replacing the
+    /// existing mapping there.
+    pub fn map_to(&self, root_vmar: &Vmar<Full>, addr: Vaddr) -> Result<()> {
+        root_vmar
+            .new_map(PAGE_SIZE, VmPerms::READ)?
+            .vmo(self.vmo.dup()?)
+            .offset(addr)
+            .can_overwrite(true)
+            .is_shared(true)
+            .build()?;
+        self.inner.lock().map_addr = Some(addr);
+        Ok(())
+    }
+
+    /// Returns the address where the page is mapped.
+    pub fn map_addr(&self) -> Option<Vaddr> {
+        self.inner.lock().map_addr
+    }
+
+    /// Forgets the mapping of the page, e.g., because the address space is cleared.
+    pub fn clear_map_addr(&self) {
+        self.inner.lock().map_addr = None;
+    }
+
+    /// Marks the address space as shared by multiple processes.
+    ///
+    /// This invalidates the page permanently.
+    pub fn set_shared(&self) {
+        let mut inner = self.inner.lock();
+        inner.is_shared = true;
+        inner.data.generation = 0;
+        self.frame.write_val(0, &inner.data.generation).unwrap();
+    }
+
+    /// Updates the identity and bumps the generation.
+    pub fn update(&self, pid: Pid, credentials: &Credentials<ReadOp>) {
+        let mut inner = self.inner.lock();
+        if inner.is_shared {
+            return;
+        }
+
+        // Update begins.
+        inner.data.generation += 1;
+        self.frame.write_val(0, &inner.data.generation).unwrap();
+
+        inner.data.pid = pid;
+        inner.data.uid = credentials.ruid().into();
+        inner.data.euid = credentials.euid().into();
+        inner.data.gid = credentials.rgid().into();
+        inner.data.egid = credentials.egid().into();
+        self.frame.write_val(0, &inner.data).unwrap();
+
+        // Update finishes.
+        inner.data.generation += 1;
+        self.frame.write_val(0, &inner.data.generation).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Migration of pages toward the NUMA nodes that access them.
+//!
+//! If a thread settled on its preferred node (see [`SchedAttr::preferred_numa_node`])
+//! faults on a page residing on another node, the page is queued to be migrated to the
+//! preferred node. The queued pages are migrated by a work item in the background, so the
+//! faulting thread is not delayed.
+//!
+//! [`SchedAttr::preferred_numa_node`]: crate::sched::SchedAttr::preferred_numa_node
+
+use alloc::collections::VecDeque;
+
+use aster_rights::Full;
+use ostd::numa::NodeId;
+use spin::Once;
+
+use crate::{
+    prelude::*,
+    thread::work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
+    vm::vmar::Vmar,
+};
+
+/// The maximum number of pages waiting to be migrated.
+///
+/// The pages faulted beyond the limit are not migrated, which bounds the
+/// memory used by the queue and the time spent on migration.
+const MAX_PENDING_PAGES: usize = 256;
+
+struct PendingPage {
+    vmar: Vmar<Full>,
+    page_addr: Vaddr,
+    node_id: NodeId,
+}
+
+static PENDING_PAGES: SpinLock<VecDeque<PendingPage>> = SpinLock::new(VecDeque::new());
+
+static MIGRATION_WORK: Once<Arc<WorkItem>> = Once::new();
+
+/// Queues the page at the page-aligned `page_addr` to be migrated to the NUMA node.
+pub fn queue_page_migration(vmar: &Vmar<Full>, page_addr: Vaddr, node_id: NodeId) {
+    {
+        let mut pending_pages = PENDING_PAGES.lock();
+        if pending_pages.len() >= MAX_PENDING_PAGES {
+            return;
+        }
+        let Ok(vmar) = vmar.dup() else {
+            return;
+        };
+        pending_pages.push_back(PendingPage {
+            vmar,
+            page_addr,
+            node_id,
+        });
+    }
+
+    let work_item = MIGRATION_WORK.call_once(|| WorkItem::new(Box::new(migrate_pending_pages)));
+    submit_work_item(work_item.clone(), WorkPriority::Normal);
+}
+
+fn migrate_pending_pages() {
+    loop {
+        let Some(page) = PENDING_PAGES.lock().pop_front() else {
+            break;
+        };
+        if let Err(err) = page.vmar.migrate_page(page.page_addr, page.node_id) {
+            debug!(
+                "failed to migrate the page at {:#x} to {:?}: {:?}",
+                page.page_addr, page.node_id, err
+            );
+        }
+    }
+}
@@ -11,11 +11,17 @@ use core::{num::NonZeroUsize, ops::Range};
 
 use align_ext::AlignExt;
 use aster_rights::Rights;
-use ostd::mm::{tlb::TlbFlushOp, PageFlags, PageProperty, VmSpace, MAX_USERSPACE_VADDR};
+use ostd::{
+    mm::{
+        tlb::TlbFlushOp, vm_space::VmItem, FrameAllocOptions, PageFlags, PageProperty, UFrame,
+        UntypedMem, VmSpace, MAX_USERSPACE_VADDR,
+    },
+    numa::{node_of_paddr, NodeId},
+};
 
 use self::{
     interval_set::{Interval, IntervalSet},
-    vm_mapping::{MappedVmo, VmMapping},
+    vm_mapping::{MappedVmo, MappingName, VmMapping, VmMappingInfo},
 };
 use crate::{
     prelude::*,
@@ -88,6 +94,63 @@ impl<R> Vmar<R> {
     pub fn resize_mapping(&self, map_addr: Vaddr, old_size: usize, new_size: usize) -> Result<()> {
         self.0.resize_mapping(map_addr, old_size, new_size)
     }
+
+    /// Marks the memory mappings in the specified range as dumpable or not.
+    ///
+    /// The non-dumpable mappings are excluded from the core dumps and cannot
+    /// be read by other processes (see [`Self::read_remote`]).
+    ///
+    /// The range's start and end addresses must be page-aligned. If the range
+    /// is not completely mapped, this method fails with `ENOMEM`.
+    pub fn set_dumpable(&self, range: Range<usize>, is_dumpable: bool) -> Result<()> {
+        debug_assert!(range.start % PAGE_SIZE == 0);
+        debug_assert!(range.end % PAGE_SIZE == 0);
+        self.0.set_dumpable(range, is_dumpable)
+    }
+
+    /// Reads the memory of the VMAR from a process other than the one that
+    /// the VMAR is bound to, e.g., through `/proc/[pid]/mem`.
+    ///
+    /// The reading stops at the first byte that is not readable or within a
+    /// non-dumpable mapping. If no byte can be read, this method fails with
+    /// `EIO`.
+    pub fn read_remote(&self, vaddr: Vaddr, writer: &mut VmWriter) -> Result<usize> {
+        self.0.read_remote(vaddr, writer)
+    }
+
+    /// Writes the memory of the VMAR from a task that does not run in the
+    /// address space, e.g., a kernel thread working on behalf of a process.
+    ///
+    /// The copy-on-write pages are copied before being written. The writing
+    /// stops at the first byte that is not writable. If no byte can be
+    /// written, this method fails with `EFAULT`.
+    pub fn write_remote(&self, vaddr: Vaddr, reader: &mut VmReader) -> Result<usize> {
+        self.0.write_remote(vaddr, reader)
+    }
+
+    /// Migrates the page at the page-aligned `page_addr` to the NUMA node.
+    ///
+    /// Only the pages that are mapped exclusively by this VMAR (e.g., the
+    /// private anonymous pages) are migrated. Other pages are left in place.
+    pub fn migrate_page(&self, page_addr: Vaddr, node_id: NodeId) -> Result<()> {
+        self.0.migrate_page(page_addr, node_id)
+    }
+
+    /// Returns the information of all the memory mappings, in the ascending
+    /// order of their addresses.
+    pub fn mappings_info(&self) -> Vec<VmMappingInfo> {
+        let inner = self.0.inner.read();
+        inner
+            .vm_mappings
+            .iter()
+            .map(|vm_mapping| vm_mapping.info())
+            .collect()
+    }
+
+    /// Returns the total size of the memory mappings in bytes.
+    pub fn total_vm(&self) -> usize {
+        self.0.inner.read().total_vm
+    }
 }
 
 pub(super) struct Vmar_ {
@@ -336,6 +399,195 @@ impl Vmar_ {
         Ok(())
     }
 
+    /// Marks the mappings in the range as dumpable or not.
+    ///
+    /// The range is ensured to be page-aligned.
+    fn set_dumpable(&self, range: Range<usize>, is_dumpable: bool) -> Result<()> {
+        let mut inner = self.inner.write();
+        if inner.count_overlap_size(range.clone()) != range.len() {
+            return_errno_with_message!(Errno::ENOMEM, "the range is not completely mapped");
+        }
+
+        let mut mappings_to_change = Vec::new();
+        for vm_mapping in inner.vm_mappings.find(&range) {
+            if vm_mapping.is_dumpable() != is_dumpable {
+                mappings_to_change.push(vm_mapping.map_to_addr());
+            }
+        }
+
+        for vm_mapping_addr in mappings_to_change {
+            let vm_mapping = inner.remove(&vm_mapping_addr).unwrap();
+            let vm_mapping_range = vm_mapping.range();
+            let intersected_range = get_intersected_range(&range, &vm_mapping_range);
+
+            let (left, taken, right) = vm_mapping.split_range(&intersected_range)?;
+            inner.insert(taken.set_dumpable(is_dumpable));
+
+            if let Some(left) = left {
+                inner.insert(left);
+            }
+            if let Some(right) = right {
+                inner.insert(right);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the memory starting from `vaddr` on behalf of another process.
+    ///
+    /// Unlike accessing the memory from the bound process, the pages that
+    /// have not been faulted in are committed here without the help of the
+    /// MMU. The reading stops at the first non-readable or non-dumpable
+    /// mapping.
+    fn read_remote(&self, vaddr: Vaddr, writer: &mut VmWriter) -> Result<usize> {
+        let inner = self.inner.read();
+
+        let mut addr = vaddr;
+        while writer.avail() > 0 {
+            let Some(vm_mapping) = inner.vm_mappings.find_one(&addr) else {
+                break;
+            };
+            if !vm_mapping.is_dumpable() || !vm_mapping.perms().contains(VmPerms::READ) {
+                break;
+            }
+
+            let page_addr = addr.align_down(PAGE_SIZE);
+            let frame = match self.query_frame(page_addr)? {
+                Some(frame) => frame,
+                None => {
+                    let page_fault_info = PageFaultInfo {
+                        address: addr,
+                        required_perms: VmPerms::READ,
+                    };
+                    if vm_mapping
+                        .handle_page_fault(&self.vm_space, &page_fault_info)
+                        .is_err()
+                    {
+                        break;
+                    }
+                    let Some(frame) = self.query_frame(page_addr)? else {
+                        break;
+                    };
+                    frame
+                }
+            };
+
+            let mut reader = frame.reader().to_fallible();
+            reader.skip(addr - page_addr);
+            addr += writer.write_fallible(&mut reader).map_err(|(err, _)| err)?;
+        }
+
+        if addr == vaddr && writer.avail() > 0 {
+            return_errno_with_message!(Errno::EIO, "the memory is not accessible");
+        }
+        Ok(addr - vaddr)
+    }
+
+    fn write_remote(&self, vaddr: Vaddr, reader: &mut VmReader) -> Result<usize> {
+        let inner = self.inner.read();
+
+        let mut addr = vaddr;
+        while reader.remain() > 0 {
+            let Some(vm_mapping) = inner.vm_mappings.find_one(&addr) else {
+                break;
+            };
+            if !vm_mapping.perms().contains(VmPerms::WRITE) {
+                break;
+            }
+
+            // A page that is mapped but not writable may be shared for copy-on-write, so the page
+            // fault handler is needed to get a private copy of it.
+            let page_addr = addr.align_down(PAGE_SIZE);
+            let frame = match self.query_writable_frame(page_addr)? {
+                Some(frame) => frame,
+                None => {
+                    let page_fault_info = PageFaultInfo {
+                        address: addr,
+                        required_perms: VmPerms::WRITE,
+                    };
+                    if vm_mapping
+                        .handle_page_fault(&self.vm_space, &page_fault_info)
+                        .is_err()
+                    {
+                        break;
+                    }
+                    let Some(frame) = self.query_writable_frame(page_addr)? else {
+                        break;
+                    };
+                    frame
+                }
+            };
+
+            let mut writer = frame.writer().to_fallible();
+            writer.skip(addr - page_addr);
+            addr += writer.write_fallible(reader).map_err(|(err, _)| err)?;
+        }
+
+        if addr == vaddr && reader.remain() > 0 {
+            return_errno_with_message!(Errno::EFAULT, "the memory is not writable");
+        }
+        Ok(addr - vaddr)
+    }
+
+    /// Returns the frame mapped at the page-aligned `page_addr` with the write permission, if any.
+    fn query_writable_frame(&self, page_addr: Vaddr) -> Result<Option<UFrame>> {
+        let mut cursor = self.vm_space.cursor(&(page_addr..page_addr + PAGE_SIZE))?;
+        match cursor.query()? {
+            VmItem::Mapped { frame, prop, .. } if prop.flags.contains(PageFlags::W) => {
+                Ok(Some(frame))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn migrate_page(&self, page_addr: Vaddr, node_id: NodeId) -> Result<()> {
+        let mut cursor = self
+            .vm_space
+            .cursor_mut(&(page_addr..page_addr + PAGE_SIZE))?;
+        let VmItem::Mapped { va, frame, .. } = cursor.query()? else {
+            return Ok(());
+        };
+        // If the frame is mapped exclusively, the only references are from the
+        // page table and `frame`. Otherwise, the frame may be accessed without
+        // the page table (e.g., via a VMO), so it cannot be replaced.
+        if frame.reference_count() != 2 || node_of_paddr(frame.start_paddr()) == node_id {
+            return Ok(());
+        }
+        let new_frame = FrameAllocOptions::new()
+            .zeroed(false)
+            .node(node_id)
+            .alloc_frame()?;
+
+        // Write-protect the page while copying it. A write to the page will
+        // fault, and the page fault handler will wait for the cursor.
+        let mut prop = None;
+        cursor.protect_next(PAGE_SIZE, |p| {
+            prop = Some(*p);
+            p.flags -= PageFlags::W;
+        });
+        cursor.flusher().issue_tlb_flush(TlbFlushOp::Address(va));
+        cursor.flusher().dispatch_tlb_flush();
+        cursor.flusher().sync_tlb_flush();
+
+        new_frame.writer().write(&mut frame.reader());
+
+        cursor.jump(va)?;
+        cursor.map(new_frame.into(), prop.unwrap());
+        cursor.flusher().sync_tlb_flush();
+
+        Ok(())
+    }
+
+    /// Returns the frame mapped at the page-aligned `page_addr`, if any.
+    fn query_frame(&self, page_addr: Vaddr) -> Result<Option<UFrame>> {
+        let mut cursor = self.vm_space.cursor(&(page_addr..page_addr + PAGE_SIZE))?;
+        match cursor.query()? {
+            VmItem::Mapped { frame, .. } => Ok(Some(frame)),
+            VmItem::NotMapped { .. } => Ok(None),
+        }
+    }
+
     /// Handles user space page fault, if the page fault is successfully handled, return Ok(()).
     pub fn handle_page_fault(&self, page_fault_info: &PageFaultInfo) -> Result<()> {
         let address = page_fault_info.address;
@@ -481,6 +733,8 @@ pub struct VmarMapOptions<'a, R1, R2> {
     is_shared: bool,
     // Whether the mapping needs to handle surrounding pages when handling page fault.
     handle_page_faults_around: bool,
+    // What the mapping is used for.
+    name: Option<MappingName>,
 }
 
 impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
@@ -503,6 +757,7 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
             can_overwrite: false,
             is_shared: false,
             handle_page_faults_around: false,
+            name: None,
         }
     }
 
@@ -594,6 +849,15 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
         self.handle_page_faults_around = true;
         self
     }
+
+    /// Sets what the mapping is used for, which is reported by
+    /// `/proc/[pid]/maps`.
+    ///
+    /// The default value is `None`, which means an anonymous mapping.
+    pub fn name(mut self, name: MappingName) -> Self {
+        self.name = Some(name);
+        self
+    }
 }
 
 impl<'a, R1, R2> VmarMapOptions<'a, R1, R2>
@@ -619,6 +883,7 @@ where
             can_overwrite,
             is_shared,
             handle_page_faults_around,
+            name,
         } = self;
 
         let mut inner = parent.0.inner.write();
@@ -664,6 +929,7 @@ where
             is_shared,
             handle_page_faults_around,
             perms,
+            name,
         );
 
         // Add the mapping to the VMAR.
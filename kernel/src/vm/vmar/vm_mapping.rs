@@ -14,6 +14,7 @@ use ostd::mm::{
 
 use super::interval_set::Interval;
 use crate::{
+    fs::path::Dentry,
     prelude::*,
     thread::exception::PageFaultInfo,
     vm::{
@@ -66,6 +67,38 @@ pub(super) struct VmMapping {
     ///
     /// All pages within the same `VmMapping` have the same permissions.
     perms: VmPerms,
+    /// Whether the mapping can be dumped.
+    ///
+    /// A mapping advised with `MADV_DONTDUMP` is excluded from the core dumps
+    /// and cannot be read by other processes through `/proc/[pid]/mem`.
+    is_dumpable: bool,
+    /// What the mapping is used for, which is reported by `/proc/[pid]/maps`.
+    name: Option<MappingName>,
+}
+
+/// What a mapping is used for, which names the mapping in `/proc/[pid]/maps`.
+#[derive(Debug, Clone)]
+pub enum MappingName {
+    /// The mapping of a file, e.g., a segment of an executable or a file
+    /// mapped by `mmap`.
+    File(Dentry),
+    /// The mapping of the heap, which is resized by `brk`.
+    Heap,
+    /// The mapping of the initial user stack.
+    Stack,
+    /// The mapping of the VDSO.
+    Vdso,
+}
+
+/// The information of a mapping, which is reported by `/proc/[pid]/maps`.
+#[derive(Debug, Clone)]
+pub struct VmMappingInfo {
+    pub range: Range<Vaddr>,
+    pub perms: VmPerms,
+    pub is_shared: bool,
+    /// The offset of the first mapped byte in the mapped VMO.
+    pub vmo_offset: usize,
+    pub name: Option<MappingName>,
 }
 
 impl Interval<Vaddr> for VmMapping {
@@ -84,6 +117,7 @@ impl VmMapping {
         is_shared: bool,
         handle_page_faults_around: bool,
         perms: VmPerms,
+        name: Option<MappingName>,
     ) -> Self {
         Self {
             map_size,
@@ -92,13 +126,21 @@ impl VmMapping {
             is_shared,
             handle_page_faults_around,
             perms,
+            is_dumpable: true,
+            name,
         }
     }
 
     pub(super) fn new_fork(&self) -> Result<VmMapping> {
         Ok(VmMapping {
+            map_size: self.map_size,
+            map_to_addr: self.map_to_addr,
             vmo: self.vmo.as_ref().map(|vmo| vmo.dup()).transpose()?,
-            ..*self
+            is_shared: self.is_shared,
+            handle_page_faults_around: self.handle_page_faults_around,
+            perms: self.perms,
+            is_dumpable: self.is_dumpable,
+            name: self.name.clone(),
         })
     }
 
@@ -121,6 +163,22 @@ impl VmMapping {
     pub fn perms(&self) -> VmPerms {
         self.perms
     }
+
+    /// Returns whether the mapping can be dumped.
+    pub fn is_dumpable(&self) -> bool {
+        self.is_dumpable
+    }
+
+    /// Returns the information of the mapping.
+    pub fn info(&self) -> VmMappingInfo {
+        VmMappingInfo {
+            range: self.range(),
+            perms: self.perms,
+            is_shared: self.is_shared,
+            vmo_offset: self.vmo.as_ref().map_or(0, |vmo| vmo.range.start),
+            name: self.name.clone(),
+        }
+    }
 }
 
 /****************************** Page faults **********************************/
@@ -364,6 +422,7 @@ impl VmMapping {
             map_to_addr: self.map_to_addr,
             map_size: NonZeroUsize::new(left_size).unwrap(),
             vmo: l_vmo,
+            name: self.name.clone(),
             ..self
         };
         let right = Self {
@@ -447,6 +506,14 @@ impl VmMapping {
 
         Self { perms, ..self }
     }
+
+    /// Changes whether the mapping can be dumped.
+    pub(super) fn set_dumpable(self, is_dumpable: bool) -> Self {
+        Self {
+            is_dumpable,
+            ..self
+        }
+    }
 }
 
 /// A wrapper that represents a mapped [`Vmo`] and provide required functionalities
@@ -334,6 +334,29 @@ impl Vmo_ {
         Ok(())
     }
 
+    /// Returns the first run of committed pages at or after `page_idx`.
+    ///
+    /// The run is returned as a range of page indices, or `None` if no page is committed.
+    pub fn next_committed_pages(&self, page_idx: usize) -> Option<Range<usize>> {
+        let nr_pages = self.size().div_ceil(PAGE_SIZE);
+        let guard = disable_preempt();
+        let mut committed = self
+            .pages
+            .range(&guard, page_idx as u64..nr_pages as u64)
+            .map(|(idx, _)| idx as usize);
+
+        let start = committed.next()?;
+        let mut end = start + 1;
+        for idx in committed {
+            if idx != end {
+                break;
+            }
+            end += 1;
+        }
+
+        Some(start..end)
+    }
+
     /// Reads the specified amount of buffer content starting from the target offset in the VMO.
     pub fn read(&self, offset: usize, writer: &mut VmWriter) -> Result<()> {
         let read_len = writer.avail().min(self.size().saturating_sub(offset));
@@ -495,6 +518,11 @@ impl<R> Vmo<R> {
     pub fn flags(&self) -> VmoFlags {
         self.0.flags()
     }
+
+    /// Returns the first run of committed pages at or after `page_idx`.
+    pub fn next_committed_pages(&self, page_idx: usize) -> Option<Range<usize>> {
+        self.0.next_committed_pages(page_idx)
+    }
 }
 
 /// Gets the page index range that contains the offset range of VMO.
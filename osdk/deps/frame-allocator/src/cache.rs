@@ -56,6 +56,7 @@ impl<const NR_CONT_FRAMES: usize, const COUNT: usize> CacheArray<NR_CONT_FRAMES,
         let allocated = super::pools::alloc(
             guard,
             Layout::from_size_align(nr_to_alloc * Self::segment_size(), PAGE_SIZE).unwrap(),
+            None,
         )?;
 
         for i in 1..nr_to_alloc {
@@ -120,7 +121,7 @@ impl CacheOfSizes {
 pub(super) fn alloc(guard: &DisabledLocalIrqGuard, layout: Layout) -> Option<Paddr> {
     let nr_frames = layout.size() / PAGE_SIZE;
     if layout.align() > layout.size() {
-        return super::pools::alloc(guard, layout);
+        return super::pools::alloc(guard, layout, None);
     }
 
     let cache_cell = CACHE.get_with(guard);
@@ -131,7 +132,7 @@ pub(super) fn alloc(guard: &DisabledLocalIrqGuard, layout: Layout) -> Option<Pad
         2 => cache.cache2.alloc(guard),
         3 => cache.cache3.alloc(guard),
         4 => cache.cache4.alloc(guard),
-        _ => super::pools::alloc(guard, layout),
+        _ => super::pools::alloc(guard, layout, None),
     }
 }
 
@@ -33,6 +33,7 @@ use core::alloc::Layout;
 use ostd::{
     cpu::PinCurrentCpu,
     mm::{frame::GlobalFrameAllocator, Paddr},
+    numa::{num_nodes, NodeId},
     trap,
 };
 
@@ -72,6 +73,20 @@ impl GlobalFrameAllocator for FrameAllocator {
         res
     }
 
+    fn alloc_on_node(&self, layout: Layout, node_id: NodeId) -> Option<Paddr> {
+        if num_nodes() == 1 {
+            return self.alloc(layout);
+        }
+
+        // The CPU-local caches are bypassed since they do not track the nodes.
+        let guard = trap::disable_local();
+        let res = pools::alloc(&guard, layout, Some(node_id));
+        if res.is_some() {
+            TOTAL_FREE_SIZE.sub(guard.current_cpu(), layout.size());
+        }
+        res
+    }
+
     fn dealloc(&self, addr: Paddr, size: usize) {
         let guard = trap::disable_local();
         TOTAL_FREE_SIZE.add(guard.current_cpu(), size);
@@ -12,6 +12,7 @@ use core::{
 use ostd::{
     cpu_local,
     mm::Paddr,
+    numa::{split_range_by_node, NodeId},
     sync::{LocalIrqDisabled, SpinLock, SpinLockGuard},
     trap::DisabledLocalIrqGuard,
 };
@@ -51,7 +52,14 @@ const MAX_BUDDY_ORDER: BuddyOrder = 32;
 /// chunks.
 const MAX_LOCAL_BUDDY_ORDER: BuddyOrder = 18;
 
-pub(super) fn alloc(guard: &DisabledLocalIrqGuard, layout: Layout) -> Option<Paddr> {
+/// Allocates a chunk for the layout.
+///
+/// If `node_id` is specified, the chunk is allocated on the NUMA node.
+pub(super) fn alloc(
+    guard: &DisabledLocalIrqGuard,
+    layout: Layout,
+    node_id: Option<NodeId>,
+) -> Option<Paddr> {
     let local_pool_cell = LOCAL_POOL.get_with(guard);
     let mut local_pool = local_pool_cell.borrow_mut();
     let mut global_pool = OnDemandGlobalLock::new();
@@ -63,12 +71,19 @@ pub(super) fn alloc(guard: &DisabledLocalIrqGuard, layout: Layout) -> Option<Pad
     let mut chunk_addr = None;
 
     if order < MAX_LOCAL_BUDDY_ORDER {
-        chunk_addr = local_pool.alloc_chunk(order);
+        chunk_addr = match node_id {
+            Some(node_id) => local_pool.alloc_chunk_on_node(order, node_id),
+            None => local_pool.alloc_chunk(order),
+        };
     }
 
     // Fall back to the global free lists if the local free lists are empty.
     if chunk_addr.is_none() {
-        chunk_addr = global_pool.get().alloc_chunk(order);
+        let pool = global_pool.get();
+        chunk_addr = match node_id {
+            Some(node_id) => pool.alloc_chunk_on_node(order, node_id),
+            None => pool.alloc_chunk(order),
+        };
     }
     // TODO: On memory pressure the global pool may be not enough. We may need
     // to merge all buddy chunks from the local pools to the global pool and
@@ -112,8 +127,12 @@ pub(super) fn dealloc(
 pub(super) fn add_free_memory(_guard: &DisabledLocalIrqGuard, addr: Paddr, size: usize) {
     let mut global_pool = OnDemandGlobalLock::new();
 
-    split_to_chunks(addr, size).for_each(|(addr, order)| {
-        global_pool.get().insert_chunk(addr, order);
+    // Split the memory at the boundaries of the NUMA nodes, so that a chunk
+    // never spans multiple nodes.
+    split_range_by_node(addr..addr + size).for_each(|(range, _)| {
+        split_to_chunks(range.start, range.len()).for_each(|(addr, order)| {
+            global_pool.get().insert_chunk(addr, order);
+        });
     });
 
     global_pool.update_global_size_if_locked();
@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::mm::{frame::linked_list::LinkedList, Paddr};
+use ostd::{
+    mm::{frame::linked_list::LinkedList, Paddr},
+    numa::{node_of_paddr, NodeId},
+};
 
 use crate::chunk::{size_of_order, BuddyOrder, FreeChunk, FreeHeadMeta};
 
@@ -41,6 +44,11 @@ impl<const MAX_ORDER: BuddyOrder> BuddySet<MAX_ORDER> {
                 break;
             }
             let buddy_addr = chunk.buddy();
+            // Chunks on different NUMA nodes are never coalesced, so that a
+            // chunk always resides on a single node.
+            if node_of_paddr(buddy_addr) != node_of_paddr(chunk.addr()) {
+                break;
+            }
             let Some(mut cursor) = list.cursor_mut_at(buddy_addr) else {
                 // The buddy is not in this free list, so we can't coalesce.
                 break;
@@ -70,15 +78,50 @@ impl<const MAX_ORDER: BuddyOrder> BuddySet<MAX_ORDER> {
             }
         }
         let non_empty = non_empty?;
-        let mut chunk = {
-            let head = self.lists[non_empty].pop_front().unwrap();
-            debug_assert_eq!(head.meta().order(), non_empty as BuddyOrder);
+        let head = self.lists[non_empty].pop_front().unwrap();
+        debug_assert_eq!(head.meta().order(), non_empty as BuddyOrder);
+
+        Some(self.split_and_take(FreeChunk::from_free_head(head), order))
+    }
+
+    /// Allocates a chunk on the NUMA node from the set.
+    ///
+    /// It is the same as [`Self::alloc_chunk`], except that the chunk is
+    /// chosen from the ones on the node. The free lists are searched for such
+    /// a chunk, so it is slower than [`Self::alloc_chunk`].
+    pub(crate) fn alloc_chunk_on_node(
+        &mut self,
+        order: BuddyOrder,
+        node_id: NodeId,
+    ) -> Option<Paddr> {
+        for i in order..MAX_ORDER {
+            let mut cursor = self.lists[i].cursor_front_mut();
+            while let Some(addr) = cursor.current_paddr() {
+                // A chunk never spans multiple nodes (see `Self::insert_chunk`).
+                if node_of_paddr(addr) != node_id {
+                    cursor.move_next();
+                    continue;
+                }
+
+                let head = cursor.take_current().unwrap();
+                debug_assert_eq!(head.meta().order(), i as BuddyOrder);
+                return Some(self.split_and_take(FreeChunk::from_free_head(head), order));
+            }
+        }
+
+        None
+    }
 
-            Some(FreeChunk::from_free_head(head))
-        };
+    /// Splits a chunk that has been removed from the set to the given order.
+    ///
+    /// The left-most sub-chunk is taken and its address is returned. The other
+    /// sub-chunks are put back to the set.
+    fn split_and_take(&mut self, chunk: FreeChunk, order: BuddyOrder) -> Paddr {
+        let chunk_order = chunk.order();
+        let mut chunk = Some(chunk);
 
         // Split the chunk.
-        for i in (order + 1..=non_empty).rev() {
+        for i in (order + 1..=chunk_order).rev() {
             let (left_sub, right_sub) = chunk.take().unwrap().split_free();
             // Push the right sub-chunk back to the free lists.
             let right_sub = right_sub.into_unique_head();
@@ -96,15 +139,16 @@ impl<const MAX_ORDER: BuddyOrder> BuddySet<MAX_ORDER> {
         let head_frame = chunk.take().unwrap().into_unique_head();
         let paddr = head_frame.start_paddr();
         head_frame.reset_as_unused(); // It will "drop" the frame without up-calling us.
-        Some(paddr)
+        paddr
     }
 }
 
 #[cfg(ktest)]
 mod test {
+    use ostd::prelude::ktest;
+
     use super::*;
     use crate::test::MockMemoryRegion;
-    use ostd::prelude::ktest;
 
     #[ktest]
     fn test_buddy_set_insert_alloc() {
@@ -145,4 +189,29 @@ mod test {
         assert!(chunk == region_start);
         assert!(set.total_size() == 0);
     }
+
+    #[ktest]
+    fn test_buddy_set_alloc_on_node() {
+        let region_order = 2;
+        let region_size = size_of_order(region_order);
+        let region = MockMemoryRegion::alloc(region_size);
+        let region_start = region.start_paddr();
+        let node_id = node_of_paddr(region_start);
+
+        let mut set = BuddySet::<3>::new_empty();
+        set.insert_chunk(region_start, region_order);
+
+        // The chunks are split in the same way as `alloc_chunk`.
+        let chunk1 = set.alloc_chunk_on_node(0, node_id).unwrap();
+        assert!(chunk1 == region_start);
+        let chunk2 = set.alloc_chunk_on_node(1, node_id).unwrap();
+        assert!(chunk2 == region_start + size_of_order(1));
+        assert!(set.total_size() == size_of_order(0));
+
+        set.insert_chunk(chunk1, 0);
+        set.insert_chunk(chunk2, 1);
+        assert!(set.total_size() == region_size);
+        let chunk = set.alloc_chunk_on_node(region_order, node_id).unwrap();
+        assert!(chunk == region_start);
+    }
 }
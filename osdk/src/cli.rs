@@ -357,6 +357,15 @@ pub struct CargoArgs {
     pub features: Vec<String>,
     #[arg(long, help = "Do not activate the `default` features", global = true)]
     pub no_default_features: bool,
+    #[arg(
+        long,
+        value_name = "NAME=y|n",
+        help = "Turn on or off kernel configuration options",
+        value_delimiter = ',',
+        num_args = 1..,
+        global = true,
+    )]
+    pub kconfig: Vec<String>,
     #[arg(
         long = "config",
         help = "Override a configuration value",
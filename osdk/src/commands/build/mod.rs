@@ -24,7 +24,7 @@ use crate::{
     },
     cli::BuildArgs,
     config::{
-        scheme::{ActionChoice, BootMethod},
+        scheme::{render_kconfig, ActionChoice, BootMethod, KCONFIG_ENV},
         Config,
     },
     error::Errno,
@@ -140,6 +140,7 @@ pub fn do_cached_build(
         &build.profile,
         &build.features[..],
         build.no_default_features,
+        &render_kconfig(&build.kconfig),
         &build.override_configs[..],
         &cargo_target_directory,
         rustflags,
@@ -192,6 +193,7 @@ fn build_kernel_elf(
     profile: &str,
     features: &[String],
     no_default_features: bool,
+    kconfig: &str,
     override_configs: &[String],
     cargo_target_directory: impl AsRef<Path>,
     rustflags: &[&str],
@@ -229,6 +231,8 @@ fn build_kernel_elf(
     let mut command = cargo();
     command.env_remove("RUSTUP_TOOLCHAIN");
     command.env("RUSTFLAGS", rustflags.join(" "));
+    // The kernel can embed the effective configuration with `option_env!`.
+    command.env(KCONFIG_ENV, kconfig);
     command.arg("build");
     command.arg("--features").arg(features.join(" "));
     if no_default_features {
@@ -140,6 +140,7 @@ impl<'de> Deserialize<'de> for TomlManifest {
         enum Field {
             ProjectType,
             SupportedArchs,
+            Kconfig,
             Boot,
             Grub,
             Qemu,
@@ -152,6 +153,7 @@ impl<'de> Deserialize<'de> for TomlManifest {
         const EXPECTED: &[&str] = &[
             "project_type",
             "supported_archs",
+            "kconfig",
             "boot",
             "grub",
             "qemu",
@@ -182,6 +184,7 @@ impl<'de> Deserialize<'de> for TomlManifest {
                         match v {
                             "project_type" => Ok(Field::ProjectType),
                             "supported_archs" => Ok(Field::SupportedArchs),
+                            "kconfig" => Ok(Field::Kconfig),
                             "boot" => Ok(Field::Boot),
                             "grub" => Ok(Field::Grub),
                             "qemu" => Ok(Field::Qemu),
@@ -243,6 +246,7 @@ impl<'de> Deserialize<'de> for TomlManifest {
                             project_type = Some(value);
                         }
                         Field::SupportedArchs => match_and_add_vec!(supported_archs),
+                        Field::Kconfig => match_and_add_option!(kconfig),
                         Field::Boot => match_and_add_option!(boot),
                         Field::Grub => match_and_add_option!(grub),
                         Field::Qemu => match_and_add_option!(qemu),
@@ -187,6 +187,7 @@ impl Config {
             }
         };
         let target_arch = common_args.target_arch.unwrap_or(get_default_arch());
+        let kconfig_options = scheme.kconfig.clone().unwrap_or_default();
         let default_scheme = ActionScheme {
             boot: scheme.boot.clone(),
             grub: scheme.grub.clone(),
@@ -196,6 +197,7 @@ impl Config {
         let build = {
             let mut build = scheme.build.clone().unwrap_or_default().finalize();
             build.apply_common_args(common_args);
+            build.resolve_kconfig(&kconfig_options);
             build
         };
         let run = {
@@ -204,6 +206,7 @@ impl Config {
             apply_args_before_finalize(&mut run, common_args, scheme.work_dir.as_ref().unwrap());
             let mut run = run.finalize(target_arch);
             apply_args_after_finalize(&mut run, common_args);
+            run.build.resolve_kconfig(&kconfig_options);
             check_compatibility(run.grub.boot_protocol, run.build.encoding.clone());
             run
         };
@@ -213,6 +216,7 @@ impl Config {
             apply_args_before_finalize(&mut test, common_args, scheme.work_dir.as_ref().unwrap());
             let mut test = test.finalize(target_arch);
             apply_args_after_finalize(&mut test, common_args);
+            test.build.resolve_kconfig(&kconfig_options);
             check_compatibility(test.grub.boot_protocol, test.build.encoding.clone());
             test
         };
@@ -1,8 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use std::collections::BTreeMap;
+
 use linux_bzimage_builder::PayloadEncoding;
 
-use super::{inherit_optional, Boot, BootScheme, Grub, GrubScheme, Qemu, QemuScheme};
+use super::{
+    inherit_optional, parse_kconfig_arg, resolve_kconfig, Boot, BootScheme, Grub, GrubScheme,
+    KconfigOption, Qemu, QemuScheme,
+};
 
 use crate::{cli::CommonArgs, config::Arch};
 
@@ -19,6 +24,9 @@ pub struct BuildScheme {
     pub features: Vec<String>,
     #[serde(default)]
     pub no_default_features: bool,
+    /// The kernel configuration options to turn on or off.
+    #[serde(default)]
+    pub kconfig: BTreeMap<String, bool>,
     /// Whether to turn on the support for the
     /// [Linux legacy x86 32-bit boot protocol](https://www.kernel.org/doc/html/v5.6/x86/boot.html)
     #[serde(default)]
@@ -35,6 +43,11 @@ pub struct Build {
     pub features: Vec<String>,
     #[serde(default)]
     pub no_default_features: bool,
+    /// The values of the kernel configuration options.
+    ///
+    /// Before [`Build::resolve_kconfig`] is called, this only contains the overridden values.
+    #[serde(default)]
+    pub kconfig: BTreeMap<String, bool>,
     // The cargo `--config` values.
     pub override_configs: Vec<String>,
     #[serde(default)]
@@ -50,6 +63,7 @@ impl Default for Build {
             profile: "dev".to_string(),
             features: Vec::new(),
             no_default_features: false,
+            kconfig: BTreeMap::new(),
             override_configs: Vec::new(),
             linux_x86_legacy_boot: false,
             strip_elf: false,
@@ -65,6 +79,10 @@ impl Build {
             self.profile.clone_from(&profile);
         }
         self.features.extend(build_args.features.clone());
+        for kconfig_arg in &build_args.kconfig {
+            let (name, value) = parse_kconfig_arg(kconfig_arg);
+            self.kconfig.insert(name, value);
+        }
         self.override_configs
             .extend(build_args.override_configs.clone());
         if common_args.build_args.no_default_features {
@@ -80,6 +98,17 @@ impl Build {
             self.encoding.clone_from(&encoding);
         }
     }
+
+    /// Resolves the values of all the kernel configuration options, and activates the Cargo
+    /// features of the enabled options.
+    pub fn resolve_kconfig(&mut self, options: &BTreeMap<String, KconfigOption>) {
+        self.kconfig = resolve_kconfig(options, &self.kconfig);
+        for (name, value) in &self.kconfig {
+            if *value {
+                self.features.extend(options[name].features.iter().cloned());
+            }
+        }
+    }
 }
 
 impl BuildScheme {
@@ -93,6 +122,9 @@ impl BuildScheme {
             features
         };
         // `no_default_features` is not inherited
+        for (name, value) in &parent.kconfig {
+            self.kconfig.entry(name.clone()).or_insert(*value);
+        }
         if parent.linux_x86_legacy_boot {
             self.linux_x86_legacy_boot = true;
         }
@@ -109,6 +141,7 @@ impl BuildScheme {
             profile: self.profile.unwrap_or_else(|| "dev".to_string()),
             features: self.features,
             no_default_features: self.no_default_features,
+            kconfig: self.kconfig,
             override_configs: Vec::new(),
             linux_x86_legacy_boot: self.linux_x86_legacy_boot,
             strip_elf: self.strip_elf,
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel configuration options.
+//!
+//! A kernel configuration option is a named build-time switch declared in `OSDK.toml`:
+//!
+//! ```toml
+//! [kconfig.CVM_GUEST]
+//! description = "The guest OS support for Confidential VMs (CVMs), e.g., Intel TDX"
+//! features = ["cvm_guest"]
+//! default = false
+//! ```
+//!
+//! An option can be turned on or off in a build scheme (`build.kconfig = { CVM_GUEST = true }`)
+//! or with the command line (`--kconfig CVM_GUEST=y`). The OSDK resolves the enabled options into
+//! Cargo features, and embeds the effective configuration into the kernel with the
+//! [`KCONFIG_ENV`] environment variable, so that the kernel can report it at runtime.
+
+use std::{collections::BTreeMap, process};
+
+use crate::{error::Errno, error_msg};
+
+/// The environment variable that carries the effective configuration to the kernel build.
+pub const KCONFIG_ENV: &str = "OSDK_KCONFIG";
+
+/// The definition of a kernel configuration option.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KconfigOption {
+    /// The description of the option.
+    #[serde(default)]
+    pub description: String,
+    /// The Cargo features to activate if the option is enabled.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Whether the option is enabled by default.
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// Resolves the values of all the options.
+///
+/// The values in `overrides` take precedence over the default values. Overriding an option that
+/// is not defined is an error.
+pub fn resolve_kconfig(
+    options: &BTreeMap<String, KconfigOption>,
+    overrides: &BTreeMap<String, bool>,
+) -> BTreeMap<String, bool> {
+    for name in overrides.keys() {
+        if !options.contains_key(name) {
+            error_msg!("The kernel configuration option `{}` is not defined", name);
+            process::exit(Errno::ParseMetadata as _);
+        }
+    }
+
+    options
+        .iter()
+        .map(|(name, option)| {
+            let value = overrides.get(name).copied().unwrap_or(option.default);
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+/// Parses a command line argument in the form of `NAME=y` or `NAME=n`.
+pub fn parse_kconfig_arg(arg: &str) -> (String, bool) {
+    let value = match arg.split_once('=') {
+        Some((name, "y")) => Some((name, true)),
+        Some((name, "n")) => Some((name, false)),
+        _ => None,
+    };
+    let Some((name, value)) = value.filter(|(name, _)| !name.is_empty()) else {
+        error_msg!(
+            "Invalid kernel configuration `{}`, expected `NAME=y` or `NAME=n`",
+            arg
+        );
+        process::exit(Errno::ParseMetadata as _);
+    };
+    (name.to_string(), value)
+}
+
+/// Renders the values of the options in the format of the `.config` file of Linux.
+pub fn render_kconfig(values: &BTreeMap<String, bool>) -> String {
+    values
+        .iter()
+        .map(|(name, value)| {
+            if *value {
+                format!("CONFIG_{}=y\n", name)
+            } else {
+                format!("# CONFIG_{} is not set\n", name)
+            }
+        })
+        .collect()
+}
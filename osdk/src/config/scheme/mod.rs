@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use crate::arch::Arch;
 
@@ -10,6 +10,8 @@ mod boot;
 pub use boot::*;
 mod grub;
 pub use grub::*;
+mod kconfig;
+pub use kconfig::*;
 mod qemu;
 pub use qemu::*;
 
@@ -22,6 +24,8 @@ pub struct Scheme {
     pub work_dir: Option<PathBuf>,
     #[serde(default)]
     pub supported_archs: Vec<Arch>,
+    /// The definitions of the kernel configuration options.
+    pub kconfig: Option<BTreeMap<String, KconfigOption>>,
     pub boot: Option<BootScheme>,
     pub grub: Option<GrubScheme>,
     pub qemu: Option<QemuScheme>,
@@ -48,6 +52,7 @@ impl Scheme {
         Scheme {
             work_dir: None,
             supported_archs: vec![],
+            kconfig: None,
             boot: None,
             grub: None,
             qemu: None,
@@ -59,6 +64,14 @@ impl Scheme {
 
     pub fn inherit(&mut self, from: &Self) {
         // Supported archs are not inherited
+        if let Some(from_kconfig) = &from.kconfig {
+            let kconfig = self.kconfig.get_or_insert_with(BTreeMap::new);
+            for (name, option) in from_kconfig {
+                kconfig
+                    .entry(name.clone())
+                    .or_insert_with(|| option.clone());
+            }
+        }
         inherit_optional!(from, self, .boot);
         inherit_optional!(from, self, .grub);
         inherit_optional!(from, self, .build);
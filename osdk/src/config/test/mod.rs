@@ -56,3 +56,37 @@ fn conditional_manifest() {
 
     fs::remove_file(tmp_file).unwrap();
 }
+
+#[test]
+fn resolve_kconfig() {
+    let toml_manifest: manifest::TomlManifest = {
+        let content = include_str!("OSDK.toml.full");
+        toml::from_str(content).unwrap()
+    };
+
+    // Default scheme
+    let mut scheme = toml_manifest.get_scheme(None::<String>).clone();
+    let options = scheme.kconfig.take().unwrap();
+    let mut build = scheme.build.unwrap_or_default().finalize();
+    build.resolve_kconfig(&options);
+    assert_eq!(
+        scheme::render_kconfig(&build.kconfig),
+        "# CONFIG_CVM_GUEST is not set\nCONFIG_LOG_COLOR=y\n"
+    );
+    assert_eq!(build.features, vec!["log_color".to_string()]);
+
+    // Tdx
+    let mut scheme = toml_manifest.get_scheme(Some("tdx".to_owned())).clone();
+    scheme.inherit(&toml_manifest.default_scheme);
+    let options = scheme.kconfig.take().unwrap();
+    let mut build = scheme.build.unwrap_or_default().finalize();
+    build.resolve_kconfig(&options);
+    assert_eq!(
+        scheme::render_kconfig(&build.kconfig),
+        "CONFIG_CVM_GUEST=y\nCONFIG_LOG_COLOR=y\n"
+    );
+    assert_eq!(
+        build.features,
+        vec!["cvm_guest".to_string(), "log_color".to_string()]
+    );
+}
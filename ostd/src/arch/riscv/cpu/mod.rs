@@ -18,3 +18,26 @@ pub fn sleep_for_interrupt() {
     crate::task::atomic_mode::might_sleep();
     riscv::asm::wfi();
 }
+
+/// Serializes the instruction stream of the current CPU.
+///
+/// After this function returns, the current CPU will fetch the instructions again, so it
+/// observes the modifications to the code that are visible to it.
+pub fn sync_core() {
+    // SAFETY: `fence.i` only synchronizes the instruction fetches with the memory accesses.
+    unsafe { core::arch::asm!("fence.i") };
+}
+
+/// Returns the ID of the physical core that the current CPU belongs to.
+///
+/// The topology of RISC-V harts is not enumerated yet.
+pub(crate) fn current_core_id() -> Option<u32> {
+    None
+}
+
+/// Returns the hardware ID of the current CPU.
+///
+/// The topology of RISC-V harts is not enumerated yet.
+pub(crate) fn current_hw_id() -> Option<u32> {
+    None
+}
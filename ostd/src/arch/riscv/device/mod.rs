@@ -4,3 +4,138 @@
 //! This module mainly contains the APIs that should exposed to the device driver like PCI, RTC
 
 pub mod io_port;
+
+use alloc::vec::Vec;
+
+use fdt::node::FdtNode;
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    io::IoMem,
+    mm::page_prop::{CachePolicy, PageFlags},
+};
+
+/// [`IoMem`]s of the ARM PL061 GPIO controllers, which will be used by `aster-gpio`.
+pub static PL061_IO_MEMS: Once<Vec<IoMem>> = Once::new();
+
+/// The SiFive SPI controllers, which will be used by `aster-spi`.
+pub static SIFIVE_SPI_NODES: Once<Vec<SpiControllerNode>> = Once::new();
+
+/// An SPI controller described by the device tree.
+#[derive(Debug)]
+pub struct SpiControllerNode {
+    /// The registers of the controller.
+    pub io_mem: IoMem,
+    /// The frequency of the input clock, which is known only if the clock is a fixed clock.
+    pub clock_hz: Option<u64>,
+    /// The devices on the bus of the controller.
+    pub devices: Vec<SpiDeviceNode>,
+}
+
+/// A device on an SPI bus described by the device tree.
+#[derive(Debug)]
+pub struct SpiDeviceNode {
+    /// The first compatible string of the device.
+    pub compatible: &'static str,
+    /// The chip select of the device.
+    pub chip_select: u32,
+    /// The maximum clock frequency of the device.
+    pub max_speed_hz: Option<u32>,
+    /// Whether the clock phase is set (i.e., data are sampled on the trailing edge).
+    pub cpha: bool,
+    /// Whether the clock polarity is set (i.e., the clock is high when idle).
+    pub cpol: bool,
+    /// Whether the chip select is active high.
+    pub cs_high: bool,
+    /// Whether the least significant bit is transferred first.
+    pub lsb_first: bool,
+}
+
+pub(super) fn init() {
+    init_pl061();
+    init_sifive_spi();
+}
+
+fn init_pl061() {
+    let io_mems = DEVICE_TREE
+        .get()
+        .unwrap()
+        .all_nodes()
+        .filter(|node| is_compatible(node, "arm,pl061"))
+        .filter_map(|node| {
+            // SAFETY: The region is an MMIO region of a PL061 GPIO controller described by the
+            // device tree, which is not used by others.
+            unsafe { io_mem_of_node(&node) }
+        })
+        .collect();
+    PL061_IO_MEMS.call_once(|| io_mems);
+}
+
+fn init_sifive_spi() {
+    let device_tree = DEVICE_TREE.get().unwrap();
+
+    let nodes = device_tree
+        .all_nodes()
+        .filter(|node| is_compatible(node, "sifive,spi0"))
+        .filter_map(|node| {
+            // SAFETY: The region is an MMIO region of an SiFive SPI controller described by the
+            // device tree, which is not used by others.
+            let io_mem = unsafe { io_mem_of_node(&node) }?;
+
+            // The first cell of the `clocks` property is the phandle of the clock.
+            let clock_hz = node
+                .property("clocks")
+                .and_then(|clocks| clocks.value.get(..4)?.try_into().ok())
+                .and_then(|phandle| device_tree.find_phandle(u32::from_be_bytes(phandle)))
+                .and_then(|clock| clock.property("clock-frequency")?.as_usize())
+                .map(|freq| freq as u64);
+
+            let devices = node
+                .children()
+                .filter_map(|child| {
+                    Some(SpiDeviceNode {
+                        compatible: child.compatible()?.first(),
+                        chip_select: child.property("reg")?.as_usize()? as u32,
+                        max_speed_hz: child
+                            .property("spi-max-frequency")
+                            .and_then(|freq| freq.as_usize())
+                            .map(|freq| freq as u32),
+                        cpha: child.property("spi-cpha").is_some(),
+                        cpol: child.property("spi-cpol").is_some(),
+                        cs_high: child.property("spi-cs-high").is_some(),
+                        lsb_first: child.property("spi-lsb-first").is_some(),
+                    })
+                })
+                .collect();
+
+            Some(SpiControllerNode {
+                io_mem,
+                clock_hz,
+                devices,
+            })
+        })
+        .collect();
+    SIFIVE_SPI_NODES.call_once(|| nodes);
+}
+
+fn is_compatible(node: &FdtNode, compatible: &str) -> bool {
+    node.compatible()
+        .is_some_and(|node_compatible| node_compatible.all().any(|c| c == compatible))
+}
+
+/// Creates the [`IoMem`] of the first region of the node.
+///
+/// # Safety
+///
+/// The first region of the node must be an MMIO region that is not used by others.
+unsafe fn io_mem_of_node(node: &FdtNode) -> Option<IoMem> {
+    let region = node.reg()?.next()?;
+    let start = region.starting_address as usize;
+    let size = region.size?;
+    Some(IoMem::new(
+        start..start + size,
+        PageFlags::RW,
+        CachePolicy::Uncacheable,
+    ))
+}
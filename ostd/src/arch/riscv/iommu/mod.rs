@@ -2,7 +2,10 @@
 
 //! The IOMMU support.
 
-use crate::mm::{dma::Daddr, Paddr};
+use crate::{
+    bus::pci::PciDeviceLocation,
+    mm::{dma::Daddr, Paddr},
+};
 
 /// An enumeration representing possible errors related to IOMMU.
 #[derive(Debug)]
@@ -15,11 +18,24 @@ pub enum IommuError {
 /// # Safety
 ///
 /// Mapping an incorrect address may lead to a kernel data leak.
-pub(crate) unsafe fn map(_daddr: Daddr, _paddr: Paddr) -> Result<(), IommuError> {
+pub(crate) unsafe fn map(
+    _device: Option<PciDeviceLocation>,
+    _daddr: Daddr,
+    _paddr: Paddr,
+    _nr_pages: usize,
+) -> Result<(), IommuError> {
     Err(IommuError::NoIommu)
 }
 
-pub(crate) fn unmap(_daddr: Daddr) -> Result<(), IommuError> {
+pub(crate) fn unmap(
+    _device: Option<PciDeviceLocation>,
+    _daddr: Daddr,
+    _nr_pages: usize,
+) -> Result<(), IommuError> {
+    Err(IommuError::NoIommu)
+}
+
+pub(crate) fn attach_device(_device: PciDeviceLocation) -> Result<(), IommuError> {
     Err(IommuError::NoIommu)
 }
 
@@ -31,6 +31,7 @@ pub(crate) unsafe fn late_init_on_bsp() {
     unsafe { crate::boot::smp::boot_all_aps() };
 
     timer::init();
+    device::init();
     let _ = pci::init();
 }
 
@@ -60,6 +61,14 @@ pub fn read_random() -> Option<u64> {
     None
 }
 
+/// Reads a hardware generated 64-bit random seed.
+///
+/// Returns None if no random seed was generated.
+pub fn read_random_seed() -> Option<u64> {
+    // FIXME: Support the entropy source (the `Zkr` extension) on RISC-V platforms.
+    None
+}
+
 pub(crate) fn enable_cpu_features() {
     unsafe {
         // We adopt a lazy approach to enable the floating-point unit; it's not
@@ -5,10 +5,8 @@
 use log::warn;
 use spin::Once;
 
-use super::boot::DEVICE_TREE;
-use crate::{
-    bus::pci::PciDeviceLocation, io::IoMem, mm::VmIoOnce, prelude::*, trap::IrqLine, Error,
-};
+use super::{boot::DEVICE_TREE, irq::IrqLine};
+use crate::{bus::pci::PciDeviceLocation, cpu::CpuId, io::IoMem, mm::VmIoOnce, prelude::*, Error};
 
 static PCI_BASE_ADDR: Once<IoMem> = Once::new();
 
@@ -64,7 +62,15 @@ pub(crate) fn init() -> Result<()> {
 
 pub(crate) const MSIX_DEFAULT_MSG_ADDR: u32 = 0x2400_0000;
 
-pub(crate) fn construct_remappable_msix_address(irq: &IrqLine) -> u32 {
+/// Constructs the message address that delivers an MSI-X interrupt to the CPU.
+///
+/// Returns `None` if the CPU cannot be addressed.
+pub(crate) fn construct_msix_address(cpu: CpuId) -> Option<u32> {
+    // TODO: Deliver the interrupts to other harts via their own interrupt files.
+    (cpu == CpuId::bsp()).then_some(MSIX_DEFAULT_MSG_ADDR)
+}
+
+pub(crate) fn construct_remappable_msix_address(_irq: &IrqLine, _cpu: CpuId) -> u32 {
     unimplemented!()
 }
 
@@ -4,7 +4,7 @@
 
 use alloc::boxed::Box;
 use core::{
-    arch::x86_64::{_fxrstor64, _fxsave64, _xrstor64, _xsave64},
+    arch::x86_64::{_fxrstor64, _fxsave64, _xrstor64, _xsave64, _xsaveopt64},
     fmt::Debug,
     sync::atomic::{AtomicBool, Ordering::Relaxed},
 };
@@ -23,10 +23,11 @@ use x86_64::registers::{
 };
 
 use crate::{
-    arch::CPU_FEATURES,
+    arch::{livepatch, CPU_FEATURES},
     task::scheduler,
     trap::call_irq_callback_functions,
     user::{ReturnReason, UserContextApi, UserContextApiInternal},
+    Error, Result,
 };
 
 cfg_if! {
@@ -126,6 +127,8 @@ impl UserContextApiInternal for UserContext {
         // return when it is syscall or cpu exception type is Fault or Trap.
         let return_reason = loop {
             scheduler::might_preempt();
+            // Returning to the user space is a safe point for live patching.
+            livepatch::migrate_current();
             self.user_context.run();
 
             match CpuException::to_cpu_exception(self.user_context.trap_num as u16) {
@@ -428,6 +431,13 @@ pub struct FpuState {
     state_area: Box<XSaveArea>,
     area_size: usize,
     is_valid: AtomicBool,
+    /// Whether the buffer is left unchanged since the last time the CPU's FPU
+    /// state was restored from it.
+    ///
+    /// Only in this case can `XSAVEOPT` skip the unmodified components, since
+    /// the CPU tracks the components by the address of the last restoration
+    /// rather than the contents of the buffer.
+    is_unchanged_since_restore: AtomicBool,
 }
 
 // The legacy SSE/MMX FPU state format (as saved by `FXSAVE` and restored by the `FXRSTOR` instructions).
@@ -494,9 +504,106 @@ impl FpuState {
             state_area: XSaveArea::init(),
             area_size,
             is_valid: AtomicBool::new(true),
+            is_unchanged_since_restore: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the size in bytes of the FPU state.
+    ///
+    /// This is the size of the standard (non-compacted) `XSAVE` area if
+    /// `XSAVE` is supported, or the size of the legacy `FXSAVE` area otherwise.
+    pub fn size(&self) -> usize {
+        self.area_size
+    }
+
+    /// Returns the state components that are saved in the FPU state.
+    ///
+    /// The value is zero if `XSAVE` is not supported.
+    pub fn xfeatures(&self) -> u64 {
+        if CPU_FEATURES.get().unwrap().has_xsave() {
+            XFEATURE_MASK_USER_RESTORE & XCr0::read().bits()
+        } else {
+            0
+        }
+    }
+
+    /// Returns the FPU state as bytes.
+    ///
+    /// The bytes are only meaningful after the state is saved with [`Self::save`].
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `XSaveArea` is plain old data and is no smaller than `area_size` bytes.
+        unsafe {
+            core::slice::from_raw_parts(
+                &*self.state_area as *const XSaveArea as *const u8,
+                self.area_size,
+            )
         }
     }
 
+    /// Returns the FPU state as mutable bytes.
+    ///
+    /// The instance is marked __invalid__ until the new contents are checked
+    /// with [`Self::validate`].
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.is_valid.store(false, Relaxed);
+        self.is_unchanged_since_restore.store(false, Relaxed);
+
+        // SAFETY: `XSaveArea` is plain old data and is no smaller than `area_size` bytes.
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                &mut *self.state_area as *mut XSaveArea as *mut u8,
+                self.area_size,
+            )
+        }
+    }
+
+    /// Validates the FPU state written with [`Self::as_bytes_mut`].
+    ///
+    /// `len` is the number of bytes that are written. If it does not exceed the
+    /// size of the legacy `FXSAVE` area, only the x87 and SSE states are
+    /// considered to be present, and the other components will be reset to
+    /// their initial states.
+    ///
+    /// On success, the instance is marked __valid__ so that it can be restored.
+    /// Otherwise, the instance is reset to the initial state and remains
+    /// __invalid__.
+    pub fn validate(&mut self, len: usize) -> Result<()> {
+        if self.check_state_area(len) {
+            self.is_valid.store(true, Relaxed);
+            return Ok(());
+        }
+
+        self.state_area = XSaveArea::init();
+        Err(Error::InvalidArgs)
+    }
+
+    fn check_state_area(&mut self, len: usize) -> bool {
+        /// The x87 and SSE states, which are contained in the legacy `FXSAVE` area.
+        const XFEATURE_MASK_FPSSE: u64 = 0b11;
+
+        let area = &mut *self.state_area;
+
+        // Setting the reserved bits of MXCSR causes #GP when the state is restored.
+        if area.fxsave_area.mxcsr & !MXCSR_FEATURE_MASK.get().unwrap() != 0 {
+            return false;
+        }
+
+        if !CPU_FEATURES.get().unwrap().has_xsave() {
+            return true;
+        }
+
+        if len <= size_of::<FxSaveArea>() {
+            area.features = XFEATURE_MASK_FPSSE;
+            area.compaction = 0;
+            area.reserved = [0; 6];
+            return true;
+        }
+
+        // Only the user states in the standard format can be restored. Otherwise, `XRSTOR`
+        // raises #GP.
+        area.features & !self.xfeatures() == 0 && area.compaction == 0 && area.reserved == [0; 6]
+    }
+
     /// Returns whether the instance can contains valid state.
     pub fn is_valid(&self) -> bool {
         self.is_valid.load(Relaxed)
@@ -507,7 +614,11 @@ impl FpuState {
         let mem_addr = &*self.state_area as *const _ as *mut u8;
 
         if CPU_FEATURES.get().unwrap().has_xsave() {
-            unsafe { _xsave64(mem_addr, XFEATURE_MASK_USER_RESTORE) };
+            if *HAS_XSAVEOPT.get().unwrap() && self.is_unchanged_since_restore.load(Relaxed) {
+                unsafe { _xsaveopt64(mem_addr, XFEATURE_MASK_USER_RESTORE) };
+            } else {
+                unsafe { _xsave64(mem_addr, XFEATURE_MASK_USER_RESTORE) };
+            }
         } else {
             unsafe { _fxsave64(mem_addr) };
         }
@@ -534,6 +645,7 @@ impl FpuState {
         }
 
         self.is_valid.store(false, Relaxed);
+        self.is_unchanged_since_restore.store(true, Relaxed);
 
         debug!("Restore FPU state");
     }
@@ -563,6 +675,7 @@ impl Clone for FpuState {
             state_area,
             area_size: self.area_size,
             is_valid: AtomicBool::new(self.is_valid()),
+            is_unchanged_since_restore: AtomicBool::new(false),
         }
     }
 }
@@ -587,6 +700,12 @@ static XSAVE_AREA_SIZE: Once<usize> = Once::new();
 /// The max size in bytes of the XSAVE area.
 const MAX_XSAVE_AREA_SIZE: usize = 4096;
 
+/// Whether the processor supports the `XSAVEOPT` instruction.
+static HAS_XSAVEOPT: Once<bool> = Once::new();
+
+/// The bits of MXCSR that are supported by the processor.
+static MXCSR_FEATURE_MASK: Once<u32> = Once::new();
+
 pub(in crate::arch) fn enable_essential_features() {
     XSTATE_MAX_FEATURES.call_once(|| {
         const XSTATE_CPUID: u32 = 0x0000000d;
@@ -609,6 +728,13 @@ pub(in crate::arch) fn enable_essential_features() {
         size
     });
 
+    HAS_XSAVEOPT.call_once(|| {
+        let cpuid = cpuid::CpuId::new();
+        cpuid
+            .get_extended_state_info()
+            .is_some_and(|info| info.has_xsaveopt())
+    });
+
     if CPU_FEATURES.get().unwrap().has_fpu() {
         let mut cr0 = Cr0::read();
         cr0.remove(Cr0Flags::TASK_SWITCHED | Cr0Flags::EMULATE_COPROCESSOR);
@@ -619,4 +745,21 @@ pub(in crate::arch) fn enable_essential_features() {
             core::arch::asm!("fninit");
         }
     }
+
+    MXCSR_FEATURE_MASK.call_once(|| {
+        /// The default MXCSR mask if the processor does not report one.
+        const MXCSR_DEFAULT_MASK: u32 = 0xFFBF;
+
+        let mut fxsave_area = FxSaveArea {
+            mxcsr_mask: 0,
+            ..XSaveArea::init().fxsave_area
+        };
+        // SAFETY: `FXSAVE` only writes the current FPU state to the properly aligned buffer.
+        unsafe { _fxsave64(&mut fxsave_area as *mut FxSaveArea as *mut u8) };
+
+        match fxsave_area.mxcsr_mask {
+            0 => MXCSR_DEFAULT_MASK,
+            mask => mask,
+        }
+    });
 }
@@ -5,6 +5,8 @@
 pub mod context;
 pub mod local;
 
+use x86::cpuid::{CpuId, TopologyType};
+
 /// Halts the CPU.
 ///
 /// This function halts the CPU until the next interrupt is received. By
@@ -15,5 +17,37 @@ pub mod local;
 #[track_caller]
 pub fn sleep_for_interrupt() {
     crate::task::atomic_mode::might_sleep();
-    x86_64::instructions::hlt();
+    crate::arch::timer::broadcast::halt();
+}
+
+/// Serializes the instruction stream of the current CPU.
+///
+/// After this function returns, the current CPU will fetch the instructions again, so it
+/// observes the modifications to the code that are visible to it.
+pub fn sync_core() {
+    // SAFETY: `cpuid` is a serializing instruction without side effects.
+    let _ = unsafe { core::arch::x86_64::__cpuid(0) };
+}
+
+/// Returns the ID of the physical core that the current CPU belongs to.
+///
+/// The ID is derived from the x2APIC ID by stripping the bits that identify
+/// the SMT siblings. It returns `None` if the topology cannot be enumerated.
+pub(crate) fn current_core_id() -> Option<u32> {
+    let smt_level = CpuId::new()
+        .get_extended_topology_info()?
+        .find(|level| level.level_type() == TopologyType::SMT)?;
+    Some(smt_level.x2apic_id() >> smt_level.shift_right_for_next_apic_id())
+}
+
+/// Returns the hardware ID of the current CPU, i.e., its (x2)APIC ID.
+pub(crate) fn current_hw_id() -> Option<u32> {
+    let cpuid = CpuId::new();
+    if let Some(mut levels) = cpuid.get_extended_topology_info() {
+        if let Some(level) = levels.next() {
+            return Some(level.x2apic_id());
+        }
+    }
+    let feature_info = cpuid.get_feature_info()?;
+    Some(feature_info.initial_local_apic_id() as u32)
 }
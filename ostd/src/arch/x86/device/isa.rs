@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Interrupts of the ISA devices.
+
+use acpi::InterruptModel;
+
+use crate::{
+    arch::kernel::{acpi::get_platform_info, IO_APIC},
+    trap::IrqLine,
+    Error, Result,
+};
+
+/// The number of the ISA IRQs.
+const NR_ISA_IRQS: u8 = 16;
+
+/// Routes an ISA IRQ to the IRQ line.
+///
+/// The ISA IRQ is translated to the global system interrupt (GSI) according to the interrupt
+/// source overrides in the ACPI tables, and the I/O APIC entry of the GSI is enabled.
+///
+/// This method fails if the ISA IRQ is invalid or is already routed to another IRQ line.
+pub fn route_irq(isa_irq: u8, irq: IrqLine) -> Result<()> {
+    if isa_irq >= NR_ISA_IRQS {
+        return Err(Error::InvalidArgs);
+    }
+    let gsi = isa_irq_to_gsi(isa_irq);
+
+    for io_apic in IO_APIC.get().ok_or(Error::NotEnoughResources)? {
+        let mut io_apic = io_apic.lock();
+        let Some(index) = gsi.checked_sub(io_apic.interrupt_base()) else {
+            continue;
+        };
+        if index < io_apic.max_redirection_entry() as u32 {
+            return io_apic.enable(index as u8, irq);
+        }
+    }
+
+    Err(Error::InvalidArgs)
+}
+
+fn isa_irq_to_gsi(isa_irq: u8) -> u32 {
+    let Some(platform_info) = get_platform_info() else {
+        // Without ACPI tables, the ISA IRQs are identity-mapped.
+        return isa_irq as u32;
+    };
+
+    match &platform_info.interrupt_model {
+        InterruptModel::Apic(apic) => apic
+            .interrupt_source_overrides
+            .iter()
+            .find(|source_override| source_override.isa_source == isa_irq)
+            .map_or(isa_irq as u32, |source_override| {
+                source_override.global_system_interrupt
+            }),
+        _ => isa_irq as u32,
+    }
+}
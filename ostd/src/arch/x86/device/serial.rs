@@ -5,8 +5,12 @@
 #![expect(dead_code)]
 
 use crate::{
-    arch::device::io_port::{ReadWriteAccess, WriteOnlyAccess},
+    arch::{
+        device::io_port::{ReadWriteAccess, WriteOnlyAccess},
+        kernel::acpi::spcr::Spcr,
+    },
     io::IoPort,
+    mm::Paddr,
 };
 /// A serial port.
 ///
@@ -93,3 +97,68 @@ impl SerialPort {
         self.line_status.read()
     }
 }
+
+/// The base address of the registers of a serial port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialPortBase {
+    /// The registers are in the I/O port space.
+    Pio(u16),
+    /// The registers are memory-mapped.
+    Mmio(Paddr),
+}
+
+/// A serial port that the firmware uses as the console.
+#[derive(Debug, Clone, Copy)]
+pub struct FirmwareSerialPort {
+    /// The base address of the registers.
+    pub base: SerialPortBase,
+    /// The log2 of the distance between two adjacent registers in bytes.
+    pub reg_shift: u8,
+    /// The ISA IRQ of the port, if any.
+    pub isa_irq: Option<u8>,
+    /// The baud rate that the firmware has configured, if any.
+    pub baud_rate: Option<u32>,
+}
+
+/// Returns the serial port that the firmware uses as the console.
+///
+/// The port is declared in the ACPI Serial Port Console Redirection (SPCR) table. Only the ports
+/// that are compatible with 16550 are returned.
+pub fn firmware_console_port() -> Option<FirmwareSerialPort> {
+    // The interface types that are compatible with 16550.
+    const INTERFACE_16550: u8 = 0x00;
+    const INTERFACE_16450: u8 = 0x01;
+    const INTERFACE_16550_GAS: u8 = 0x12;
+    // The interrupt type indicating that the port uses a PC-AT-compatible IRQ.
+    const INTERRUPT_TYPE_8259: u8 = 1 << 0;
+
+    let spcr = Spcr::new()?;
+    if !matches!(
+        spcr.interface_type,
+        INTERFACE_16550 | INTERFACE_16450 | INTERFACE_16550_GAS
+    ) {
+        return None;
+    }
+
+    let base = match spcr.address_space {
+        0 => SerialPortBase::Mmio(spcr.address as Paddr),
+        1 => SerialPortBase::Pio(u16::try_from(spcr.address).ok()?),
+        _ => return None,
+    };
+    let reg_shift = if spcr.bit_width == 32 { 2 } else { 0 };
+    let isa_irq = (spcr.interrupt_type & INTERRUPT_TYPE_8259 != 0).then_some(spcr.irq);
+    let baud_rate = match spcr.baud_rate {
+        3 => Some(9600),
+        4 => Some(19200),
+        6 => Some(57600),
+        7 => Some(115200),
+        _ => None,
+    };
+
+    Some(FirmwareSerialPort {
+        base,
+        reg_shift,
+        isa_irq,
+        baud_rate,
+    })
+}
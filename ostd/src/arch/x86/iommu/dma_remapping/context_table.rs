@@ -46,6 +46,8 @@ pub struct RootTable {
 #[derive(Debug)]
 pub enum ContextTableError {
     InvalidDeviceId,
+    /// The device has not been attached.
+    DeviceNotAttached,
     /// Error when modifying the page table
     ModificationError(PageTableError),
 }
@@ -62,6 +64,40 @@ impl RootTable {
         }
     }
 
+    /// Attaches a device with a new page table, which is tagged with the domain ID in the
+    /// caches of the IOMMU.
+    ///
+    /// Returns `false` if the device has been attached.
+    pub(super) fn attach_device(
+        &mut self,
+        device: PciDeviceLocation,
+        domain_id: u16,
+    ) -> Result<bool, ContextTableError> {
+        if device.device >= 32 || device.function >= 8 {
+            return Err(ContextTableError::InvalidDeviceId);
+        }
+
+        Ok(self
+            .get_or_create_context_table(device)
+            .create_page_table(device, domain_id))
+    }
+
+    /// Returns true if the device has been attached.
+    pub(super) fn is_attached(&self, device: PciDeviceLocation) -> bool {
+        if device.device >= 32 || device.function >= 8 {
+            return false;
+        }
+
+        let bus_entry = self
+            .root_frame
+            .read_val::<RootEntry>(device.bus as usize * size_of::<RootEntry>())
+            .unwrap();
+        bus_entry.is_present()
+            && self.context_tables[&(bus_entry.addr() as usize)]
+                .read_entry(device)
+                .is_present()
+    }
+
     /// Mapping device address to physical address.
     ///
     /// # Safety
@@ -73,8 +109,8 @@ impl RootTable {
         daddr: Daddr,
         paddr: Paddr,
     ) -> Result<(), ContextTableError> {
-        if device.device >= 32 || device.function >= 8 {
-            return Err(ContextTableError::InvalidDeviceId);
+        if !self.is_attached(device) {
+            return Err(ContextTableError::DeviceNotAttached);
         }
 
         self.get_or_create_context_table(device)
@@ -88,8 +124,8 @@ impl RootTable {
         device: PciDeviceLocation,
         daddr: Daddr,
     ) -> Result<(), ContextTableError> {
-        if device.device >= 32 || device.function >= 8 {
-            return Err(ContextTableError::InvalidDeviceId);
+        if !self.is_attached(device) {
+            return Err(ContextTableError::DeviceNotAttached);
         }
 
         self.get_or_create_context_table(device)
@@ -98,43 +134,6 @@ impl RootTable {
         Ok(())
     }
 
-    /// Specifies the device page table instead of creating a page table if not exists.
-    ///
-    /// This will be useful if we want all the devices to use the same page table.
-    /// The original page table will be overwritten.
-    pub(super) fn specify_device_page_table(
-        &mut self,
-        device_id: PciDeviceLocation,
-        page_table: PageTable<DeviceMode, PageTableEntry, PagingConsts>,
-    ) {
-        let context_table = self.get_or_create_context_table(device_id);
-
-        let bus_entry = context_table
-            .entries_frame
-            .read_val::<ContextEntry>(
-                (device_id.device as usize * 8 + device_id.function as usize)
-                    * size_of::<ContextEntry>(),
-            )
-            .unwrap();
-
-        if bus_entry.is_present() {
-            panic!("existing device page tables should not be overridden");
-        }
-
-        // Activate page table.
-        let address = unsafe { page_table.root_paddr() };
-        context_table.page_tables.insert(address, page_table);
-        let entry = ContextEntry(address as u128 | 1 | 0x1_0000_0000_0000_0000);
-        context_table
-            .entries_frame
-            .write_val::<ContextEntry>(
-                (device_id.device as usize * 8 + device_id.function as usize)
-                    * size_of::<ContextEntry>(),
-                &entry,
-            )
-            .unwrap();
-    }
-
     fn get_or_create_context_table(&mut self, device_id: PciDeviceLocation) -> &mut ContextTable {
         let bus_entry = self
             .root_frame
@@ -254,35 +253,47 @@ impl ContextTable {
         self.entries_frame.start_paddr()
     }
 
-    fn get_or_create_page_table(
+    fn entry_offset(device: PciDeviceLocation) -> usize {
+        (device.device as usize * 8 + device.function as usize) * size_of::<ContextEntry>()
+    }
+
+    fn read_entry(&self, device: PciDeviceLocation) -> ContextEntry {
+        self.entries_frame
+            .read_val::<ContextEntry>(Self::entry_offset(device))
+            .unwrap()
+    }
+
+    /// Creates the page table of the device if it does not exist.
+    ///
+    /// Returns `false` if the page table exists.
+    fn create_page_table(&mut self, device: PciDeviceLocation, domain_id: u16) -> bool {
+        if self.read_entry(device).is_present() {
+            return false;
+        }
+
+        let table = PageTable::<DeviceMode, PageTableEntry, PagingConsts>::empty();
+        let address = unsafe { table.root_paddr() };
+        self.page_tables.insert(address, table);
+        // The address width is 39 bits (i.e., 3-level page tables), and the faults are reported.
+        let entry = ContextEntry(
+            address as u128 | 1 | 0x1_0000_0000_0000_0000 | ((domain_id as u128) << 72),
+        );
+        self.entries_frame
+            .write_val::<ContextEntry>(Self::entry_offset(device), &entry)
+            .unwrap();
+        true
+    }
+
+    /// Returns the page table of the device, which must exist.
+    fn page_table_mut(
         &mut self,
         device: PciDeviceLocation,
     ) -> &mut PageTable<DeviceMode, PageTableEntry, PagingConsts> {
-        let bus_entry = self
-            .entries_frame
-            .read_val::<ContextEntry>(
-                (device.device as usize * 8 + device.function as usize) * size_of::<ContextEntry>(),
-            )
-            .unwrap();
-
-        if !bus_entry.is_present() {
-            let table = PageTable::<DeviceMode, PageTableEntry, PagingConsts>::empty();
-            let address = unsafe { table.root_paddr() };
-            self.page_tables.insert(address, table);
-            let entry = ContextEntry(address as u128 | 3 | 0x1_0000_0000_0000_0000);
-            self.entries_frame
-                .write_val::<ContextEntry>(
-                    (device.device as usize * 8 + device.function as usize)
-                        * size_of::<ContextEntry>(),
-                    &entry,
-                )
-                .unwrap();
-            self.page_tables.get_mut(&address).unwrap()
-        } else {
-            self.page_tables
-                .get_mut(&(bus_entry.second_stage_pointer() as usize))
-                .unwrap()
-        }
+        let entry = self.read_entry(device);
+        debug_assert!(entry.is_present());
+        self.page_tables
+            .get_mut(&(entry.second_stage_pointer() as usize))
+            .unwrap()
     }
 
     /// # Safety
@@ -303,7 +314,7 @@ impl ContextTable {
             paddr,
             device
         );
-        self.get_or_create_page_table(device)
+        self.page_table_mut(device)
             .map(
                 &(daddr..daddr + PAGE_SIZE),
                 &(paddr..paddr + PAGE_SIZE),
@@ -322,7 +333,7 @@ impl ContextTable {
             return Err(ContextTableError::InvalidDeviceId);
         }
         trace!("Unmapping Daddr: {:x?} for device: {:x?}", daddr, device);
-        let pt = self.get_or_create_page_table(device);
+        let pt = self.page_table_mut(device);
         let mut cursor = pt.cursor_mut(&(daddr..daddr + PAGE_SIZE)).unwrap();
         unsafe {
             let result = cursor.take_next(PAGE_SIZE);
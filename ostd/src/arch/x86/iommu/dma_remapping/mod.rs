@@ -1,15 +1,33 @@
 // SPDX-License-Identifier: MPL-2.0
 
-pub use context_table::RootTable;
-use log::info;
-use second_stage::{DeviceMode, PageTableEntry, PagingConsts};
+//! DMA remapping.
+//!
+//! Each PCI device has its own page table, so a device can only access the memory that is mapped
+//! for it. A mapping is either made for a single device, or shared by all the devices. The shared
+//! mappings are added to the page table of a device when the device is attached.
+//!
+//! A device is attached when its bus mastering is enabled, since it cannot issue DMA requests
+//! before that. The DMA requests from the devices that are not attached are blocked.
+
+use alloc::{collections::BTreeSet, vec::Vec};
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use context_table::{ContextTableError, RootTable};
+use log::{info, warn};
 use spin::Once;
 
 use super::IommuError;
 use crate::{
-    arch::iommu::registers::IOMMU_REGS,
+    arch::{
+        iommu::registers::IOMMU_REGS,
+        kernel::acpi::{
+            dmar::{Dmar, Remapping},
+            remapping::DeviceScope,
+        },
+    },
     bus::pci::PciDeviceLocation,
-    mm::{Daddr, PageTable},
+    mm::{Daddr, PAGE_SIZE},
     prelude::Paddr,
     sync::{LocalIrqDisabled, SpinLock},
 };
@@ -18,63 +36,249 @@ mod context_table;
 mod second_stage;
 
 pub fn has_dma_remapping() -> bool {
-    PAGE_TABLE.get().is_some()
+    DMA_REMAPPING.get().is_some()
+}
+
+/// Attaches a PCI device, so that it can access the memory that is mapped for it.
+///
+/// This does nothing if the device has been attached or there is no DMA remapping.
+pub fn attach_device(device: PciDeviceLocation) -> Result<(), IommuError> {
+    let Some(remapping) = DMA_REMAPPING.get() else {
+        return Err(IommuError::NoIommu);
+    };
+    remapping.lock().attach_device(device)
 }
 
 /// Mapping device address to physical address.
 ///
+/// If `device` is `None`, the pages are mapped for all the devices. Otherwise, they are only
+/// mapped for the device, which will be attached if it has not been.
+///
 /// # Safety
 ///
 /// Mapping an incorrect address may lead to a kernel data leak.
-pub unsafe fn map(daddr: Daddr, paddr: Paddr) -> Result<(), IommuError> {
-    let Some(table) = PAGE_TABLE.get() else {
+pub unsafe fn map(
+    device: Option<PciDeviceLocation>,
+    daddr: Daddr,
+    paddr: Paddr,
+    nr_pages: usize,
+) -> Result<(), IommuError> {
+    let Some(remapping) = DMA_REMAPPING.get() else {
         return Err(IommuError::NoIommu);
     };
-    // The page table of all devices is the same. So we can use any device ID.
-    table
-        .lock()
-        .map(PciDeviceLocation::zero(), daddr, paddr)
-        .map_err(|err| match err {
-            context_table::ContextTableError::InvalidDeviceId => unreachable!(),
-            context_table::ContextTableError::ModificationError(err) => {
-                IommuError::ModificationError(err)
+    let mut remapping = remapping.lock();
+
+    let devices = match device {
+        Some(device) => {
+            remapping.attach_device(device)?;
+            alloc::vec![device]
+        }
+        None => {
+            for i in 0..nr_pages {
+                remapping.shared_pages.insert(daddr + i * PAGE_SIZE);
             }
-        })
+            remapping.devices.clone()
+        }
+    };
+
+    for device in devices {
+        for i in 0..nr_pages {
+            // SAFETY: The safety is upheld by the caller.
+            unsafe {
+                remapping
+                    .root_table
+                    .map(device, daddr + i * PAGE_SIZE, paddr + i * PAGE_SIZE)
+                    .map_err(IommuError::from)?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
-pub fn unmap(daddr: Daddr) -> Result<(), IommuError> {
-    let Some(table) = PAGE_TABLE.get() else {
+/// Unmapping device address.
+///
+/// The `device` should be the same as the one used to map the pages. The unmapped pages can no
+/// longer be accessed by the devices when this function returns.
+pub fn unmap(
+    device: Option<PciDeviceLocation>,
+    daddr: Daddr,
+    nr_pages: usize,
+) -> Result<(), IommuError> {
+    let Some(remapping) = DMA_REMAPPING.get() else {
         return Err(IommuError::NoIommu);
     };
-    // The page table of all devices is the same. So we can use any device ID.
-    table
-        .lock()
-        .unmap(PciDeviceLocation::zero(), daddr)
-        .map_err(|err| match err {
-            context_table::ContextTableError::InvalidDeviceId => unreachable!(),
-            context_table::ContextTableError::ModificationError(err) => {
-                IommuError::ModificationError(err)
+    let mut remapping = remapping.lock();
+
+    let devices = match device {
+        Some(device) => alloc::vec![device],
+        None => {
+            for i in 0..nr_pages {
+                remapping.shared_pages.remove(&(daddr + i * PAGE_SIZE));
             }
-        })
+            remapping.devices.clone()
+        }
+    };
+
+    for device in devices {
+        for i in 0..nr_pages {
+            remapping
+                .root_table
+                .unmap(device, daddr + i * PAGE_SIZE)
+                .map_err(IommuError::from)?;
+        }
+    }
+    IOMMU_REGS.get().unwrap().lock().invalidate_iotlb();
+
+    Ok(())
 }
 
 pub fn init() {
-    // Create Root Table instance
-    let mut root_table = RootTable::new();
-    // For all PCI Device, use the same page table.
-    let page_table = PageTable::<DeviceMode, PageTableEntry, PagingConsts>::empty();
-    for table in PciDeviceLocation::all() {
-        root_table.specify_device_page_table(table, unsafe { page_table.shallow_copy() })
-    }
-    PAGE_TABLE.call_once(|| SpinLock::new(root_table));
+    let Some(regs) = IOMMU_REGS.get() else {
+        return;
+    };
+    let mut regs = regs.lock();
+
+    let nr_domain_bits = 4 + 2 * regs.read_capability().domain_support_number();
+    let max_domain_id = ((1u32 << nr_domain_bits) - 1).min(u16::MAX as u32) as u16;
+
+    let root_table = RootTable::new();
+    let root_table_paddr = root_table.root_paddr();
+    DMA_REMAPPING.call_once(|| {
+        SpinLock::new(DmaRemapping {
+            root_table,
+            devices: Vec::new(),
+            shared_pages: BTreeSet::new(),
+            reserved_regions: reserved_regions(),
+            max_domain_id,
+        })
+    });
 
     // Enable DMA remapping
-    let mut iommu_regs = IOMMU_REGS.get().unwrap().lock();
-    iommu_regs.enable_dma_remapping(PAGE_TABLE.get().unwrap());
+    regs.enable_dma_remapping(root_table_paddr);
     info!("[IOMMU] DMA remapping enabled");
 }
 
+/// The state of DMA remapping.
+struct DmaRemapping {
+    root_table: RootTable,
+    /// The devices that have been attached, whose domain IDs are their indexes plus one.
+    devices: Vec<PciDeviceLocation>,
+    /// The pages that are mapped for all the devices.
+    ///
+    /// The device addresses are always the same as the physical addresses.
+    shared_pages: BTreeSet<Daddr>,
+    /// The memory regions reserved by the firmware, and the devices that require access to them.
+    reserved_regions: Vec<(DeviceScope, Range<Paddr>)>,
+    max_domain_id: u16,
+}
+
+impl DmaRemapping {
+    fn attach_device(&mut self, device: PciDeviceLocation) -> Result<(), IommuError> {
+        if self.root_table.is_attached(device) {
+            return Ok(());
+        }
+
+        // Domain ID 0 is reserved if the caching mode is enabled, so it is never used.
+        let domain_id = self.devices.len() + 1;
+        if domain_id > self.max_domain_id as usize {
+            warn!("[IOMMU] No domain ID for device {:x?}", device);
+            return Err(IommuError::NoDomainId);
+        }
+        self.root_table
+            .attach_device(device, domain_id as u16)
+            .map_err(IommuError::from)?;
+        self.devices.push(device);
+
+        for daddr in self.shared_pages.iter() {
+            // SAFETY: The page has been mapped for all the devices.
+            unsafe {
+                self.root_table
+                    .map(device, *daddr, *daddr)
+                    .map_err(IommuError::from)?;
+            }
+        }
+        for (scope, region) in self.reserved_regions.iter() {
+            if resolve_device_scope(scope) != Some(device) {
+                continue;
+            }
+            for paddr in region.clone().step_by(PAGE_SIZE) {
+                // SAFETY: The firmware reports that the device requires access to the region.
+                unsafe {
+                    self.root_table
+                        .map(device, paddr, paddr)
+                        .map_err(IommuError::from)?;
+                }
+            }
+        }
+
+        IOMMU_REGS
+            .get()
+            .unwrap()
+            .lock()
+            .invalidate_context_cache_and_iotlb();
+        Ok(())
+    }
+}
+
+/// Collects the memory regions reported by the RMRR structures in the DMAR table.
+fn reserved_regions() -> Vec<(DeviceScope, Range<Paddr>)> {
+    let Some(dmar) = Dmar::new() else {
+        return Vec::new();
+    };
+
+    let mut regions = Vec::new();
+    for remapping in dmar.remapping_iter() {
+        let Remapping::Rmrr(rmrr) = remapping else {
+            continue;
+        };
+        let region = rmrr.memory_region();
+        let start = (region.start as Paddr).align_down(PAGE_SIZE);
+        let end = (region.end as Paddr).align_up(PAGE_SIZE);
+        for scope in rmrr.device_scopes() {
+            if scope.is_pci_endpoint() {
+                regions.push((scope.clone(), start..end));
+            }
+        }
+    }
+    regions
+}
+
+/// Resolves the location of the device in the device scope, by walking the bridges on the path.
+fn resolve_device_scope(scope: &DeviceScope) -> Option<PciDeviceLocation> {
+    /// The offset of the secondary bus number in the configuration space of a PCI-to-PCI bridge.
+    const SECONDARY_BUS_OFFSET: u16 = 0x19;
+
+    let (last, bridges) = scope.path().split_last()?;
+    let mut bus = scope.start_bus_number();
+    for (device, function) in bridges {
+        let bridge = PciDeviceLocation {
+            bus,
+            device: *device,
+            function: *function,
+        };
+        bus = bridge.read8(SECONDARY_BUS_OFFSET);
+    }
+
+    Some(PciDeviceLocation {
+        bus,
+        device: last.0,
+        function: last.1,
+    })
+}
+
+impl From<ContextTableError> for IommuError {
+    fn from(err: ContextTableError) -> Self {
+        match err {
+            ContextTableError::InvalidDeviceId | ContextTableError::DeviceNotAttached => {
+                IommuError::InvalidDevice
+            }
+            ContextTableError::ModificationError(err) => IommuError::ModificationError(err),
+        }
+    }
+}
+
 // TODO: Currently `map()` or `unmap()` could be called in both task and interrupt
 // contexts (e.g., within the virtio-blk module), potentially leading to deadlocks.
 // Once this issue is resolved, `LocalIrqDisabled` is no longer needed.
-static PAGE_TABLE: Once<SpinLock<RootTable, LocalIrqDisabled>> = Once::new();
+static DMA_REMAPPING: Once<SpinLock<DmaRemapping, LocalIrqDisabled>> = Once::new();
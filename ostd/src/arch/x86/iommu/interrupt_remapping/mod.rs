@@ -73,6 +73,17 @@ pub fn alloc_irt_entry() -> Option<Arc<SpinLock<IrtEntryHandle, LocalIrqDisabled
     page_table.alloc()
 }
 
+/// Invalidates the cached entries of the interrupt remapping table.
+///
+/// This should be called after a present entry is modified.
+pub fn invalidate_irt_cache() {
+    IOMMU_REGS
+        .get()
+        .unwrap()
+        .lock()
+        .invalidate_interrupt_entry_cache();
+}
+
 pub(super) fn init() {
     let mut iommu_regs = IOMMU_REGS.get().unwrap().lock();
 
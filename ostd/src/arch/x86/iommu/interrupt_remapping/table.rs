@@ -157,9 +157,12 @@ impl IrtEntry {
     }
 
     /// Enables this entry with no validation,
-    /// DST = 0, IM = 0, DLM = 0, TM = 0, RH = 0, DM = 0, FPD = 1, P = 1
-    pub fn enable_default(&mut self, vector: u32) {
-        self.0 = 0b11 | ((vector as u128) << 16);
+    /// DST = `destination`, IM = 0, DLM = 0, TM = 0, RH = 0, DM = 0, FPD = 1, P = 1
+    ///
+    /// The destination is the APIC ID of the target processor in the x2APIC mode, which is the
+    /// mode of the table.
+    pub fn enable_default(&mut self, vector: u32, destination: u32) {
+        self.0 = 0b11 | ((vector as u128) << 16) | ((destination as u128) << 32);
     }
 
     pub fn source_validation_type(&self) -> SourceValidationType {
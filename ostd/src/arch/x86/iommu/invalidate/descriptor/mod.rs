@@ -1,5 +1,29 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub struct ContextCache(pub u128);
+
+impl ContextCache {
+    const INVALIDATION_TYPE: u128 = 1;
+    const GLOBAL_GRANULARITY: u128 = 1 << 4;
+
+    pub fn global_invalidation() -> Self {
+        Self(Self::INVALIDATION_TYPE | Self::GLOBAL_GRANULARITY)
+    }
+}
+
+pub struct Iotlb(pub u128);
+
+impl Iotlb {
+    const INVALIDATION_TYPE: u128 = 2;
+    const GLOBAL_GRANULARITY: u128 = 1 << 4;
+    /// Drains the pending DMA reads and writes, so that they do not use the stale translations.
+    const DRAIN_READS_WRITES: u128 = 0b11 << 6;
+
+    pub fn global_invalidation() -> Self {
+        Self(Self::INVALIDATION_TYPE | Self::GLOBAL_GRANULARITY | Self::DRAIN_READS_WRITES)
+    }
+}
+
 pub struct InterruptEntryCache(pub u128);
 
 impl InterruptEntryCache {
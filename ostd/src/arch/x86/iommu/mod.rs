@@ -8,8 +8,10 @@ mod interrupt_remapping;
 mod invalidate;
 mod registers;
 
-pub(crate) use dma_remapping::{has_dma_remapping, map, unmap};
-pub(crate) use interrupt_remapping::{alloc_irt_entry, has_interrupt_remapping, IrtEntryHandle};
+pub(crate) use dma_remapping::{attach_device, has_dma_remapping, map, unmap};
+pub(crate) use interrupt_remapping::{
+    alloc_irt_entry, has_interrupt_remapping, invalidate_irt_cache, IrtEntryHandle,
+};
 
 use crate::{io::IoMemAllocatorBuilder, mm::page_table::PageTableError};
 
@@ -18,6 +20,10 @@ use crate::{io::IoMemAllocatorBuilder, mm::page_table::PageTableError};
 pub enum IommuError {
     /// No IOMMU is available.
     NoIommu,
+    /// The device is invalid or has not been attached.
+    InvalidDevice,
+    /// No domain ID is available for a new device.
+    NoDomainId,
     /// Error encountered during modification of the page table.
     ModificationError(PageTableError),
 }
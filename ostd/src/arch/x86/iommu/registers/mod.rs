@@ -24,23 +24,20 @@ use volatile::{
     VolatileRef,
 };
 
-use super::{
-    dma_remapping::RootTable, interrupt_remapping::IntRemappingTable, invalidate::queue::Queue,
-    IommuError,
-};
+use super::{interrupt_remapping::IntRemappingTable, invalidate::queue::Queue, IommuError};
 use crate::{
     arch::{
         iommu::{
             fault,
             invalidate::{
-                descriptor::{InterruptEntryCache, InvalidationWait},
+                descriptor::{ContextCache, InterruptEntryCache, InvalidationWait, Iotlb},
                 QUEUE,
             },
         },
         kernel::acpi::dmar::{Dmar, Remapping},
     },
     io::IoMemAllocatorBuilder,
-    mm::{paddr_to_vaddr, PAGE_SIZE},
+    mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
     sync::{LocalIrqDisabled, SpinLock},
 };
 
@@ -106,18 +103,18 @@ impl IommuRegisters {
         GlobalStatus::from_bits_truncate(self.global_status.as_ptr().read())
     }
 
-    /// Enables DMA remapping with static RootTable
-    pub(super) fn enable_dma_remapping(
-        &mut self,
-        root_table: &'static SpinLock<RootTable, LocalIrqDisabled>,
-    ) {
+    /// Enables DMA remapping with the root table at the physical address.
+    pub(super) fn enable_dma_remapping(&mut self, root_table_paddr: Paddr) {
         // Set root table address
         self.root_table_address
             .as_mut_ptr()
-            .write(root_table.lock().root_paddr() as u64);
+            .write(root_table_paddr as u64);
         self.write_global_command(GlobalCommand::SRTP, true);
         while !self.read_global_status().contains(GlobalStatus::RTPS) {}
 
+        // The caches may hold the translations made before the root table is set.
+        self.invalidate_context_cache_and_iotlb();
+
         // Enable DMA remapping
         self.write_global_command(GlobalCommand::TE, true);
         while !self.read_global_status().contains(GlobalStatus::TES) {}
@@ -140,31 +137,7 @@ impl IommuRegisters {
         self.write_global_command(GlobalCommand::IRE, true);
         while !self.read_global_status().contains(GlobalStatus::IRES) {}
 
-        // Invalidate interrupt cache
-        if self.read_global_status().contains(GlobalStatus::QIES) {
-            let mut queue = QUEUE.get().unwrap().lock();
-
-            // Construct global invalidation of interrupt cache and invalidation wait.
-            queue.append_descriptor(InterruptEntryCache::global_invalidation().0);
-            let tail = queue.tail();
-            self.invalidate
-                .queue_tail
-                .as_mut_ptr()
-                .write((tail << 4) as u64);
-            while (self.invalidate.queue_head.as_ptr().read() >> 4) + 1 == tail as u64 {}
-
-            // We need to set the interrupt flag so that the `Invalidation Completion Status Register` can report the completion status.
-            queue.append_descriptor(InvalidationWait::with_interrupt_flag().0);
-            self.invalidate
-                .queue_tail
-                .as_mut_ptr()
-                .write((queue.tail() << 4) as u64);
-
-            // Wait for completion
-            while self.invalidate.completion_status.as_ptr().read() == 0 {}
-        } else {
-            self.global_invalidation()
-        }
+        self.invalidate_interrupt_entry_cache();
 
         // Disable Compatibility format interrupts
         if self.read_global_status().contains(GlobalStatus::CFIS) {
@@ -173,6 +146,64 @@ impl IommuRegisters {
         }
     }
 
+    /// Invalidates the interrupt entry cache, so that the changes of the interrupt remapping
+    /// table take effect.
+    pub(super) fn invalidate_interrupt_entry_cache(&mut self) {
+        if !self.read_global_status().contains(GlobalStatus::QIES) {
+            self.global_invalidation();
+            return;
+        }
+
+        self.submit_invalidations(&[InterruptEntryCache::global_invalidation().0]);
+    }
+
+    /// Invalidates the context cache and the IOTLB, so that the changes of the context entries
+    /// take effect.
+    pub(super) fn invalidate_context_cache_and_iotlb(&mut self) {
+        if !self.read_global_status().contains(GlobalStatus::QIES) {
+            self.global_invalidation();
+            return;
+        }
+
+        // The IOTLB must be invalidated after the context cache.
+        self.submit_invalidations(&[
+            ContextCache::global_invalidation().0,
+            Iotlb::global_invalidation().0,
+        ]);
+    }
+
+    /// Invalidates the IOTLB, so that the unmapped pages can no longer be accessed by the
+    /// devices.
+    pub(super) fn invalidate_iotlb(&mut self) {
+        if !self.read_global_status().contains(GlobalStatus::QIES) {
+            self.global_invalidation();
+            return;
+        }
+
+        self.submit_invalidations(&[Iotlb::global_invalidation().0]);
+    }
+
+    /// Submits the invalidation descriptors to the queue, and waits until they are completed.
+    fn submit_invalidations(&mut self, descriptors: &[u128]) {
+        let mut queue = QUEUE.get().unwrap().lock();
+
+        // Clear the status of the last invalidation wait. The bit is cleared by writing one.
+        self.invalidate.completion_status.as_mut_ptr().write(1);
+
+        for descriptor in descriptors {
+            queue.append_descriptor(*descriptor);
+        }
+        // We need to set the interrupt flag so that the `Invalidation Completion Status Register` can report the completion status.
+        queue.append_descriptor(InvalidationWait::with_interrupt_flag().0);
+        self.invalidate
+            .queue_tail
+            .as_mut_ptr()
+            .write((queue.tail() << 4) as u64);
+
+        // Wait for completion
+        while self.invalidate.completion_status.as_ptr().read() == 0 {}
+    }
+
     pub(super) fn enable_queued_invalidation(&mut self, queue: &Queue) {
         assert!(self
             .read_extended_capability()
@@ -233,6 +264,9 @@ impl IommuRegisters {
             .iotlb_invalidate
             .as_mut_ptr()
             .write(0x9000_0000_0000_0000);
+
+        // Wait for invalidation complete (IVT set to 0).
+        while (self.invalidate.iotlb_invalidate.as_ptr().read() & 0x8000_0000_0000_0000) != 0 {}
     }
 
     /// Writes value to the global command register. This function will not wait until the command
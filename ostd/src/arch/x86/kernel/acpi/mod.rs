@@ -2,6 +2,8 @@
 
 pub mod dmar;
 pub mod remapping;
+pub mod spcr;
+pub mod srat;
 
 use core::ptr::NonNull;
 
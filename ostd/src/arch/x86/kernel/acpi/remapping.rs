@@ -52,6 +52,20 @@ pub struct Rmrr {
     device_scopes: Vec<DeviceScope>,
 }
 
+impl Rmrr {
+    /// Returns the range of the physical addresses of the reserved memory region.
+    pub fn memory_region(&self) -> core::ops::Range<u64> {
+        // The limit address is the last byte of the region.
+        self.header.reserved_memory_region_base_addr
+            ..self.header.reserved_memory_region_limit_addr + 1
+    }
+
+    /// Returns the devices that require access to the reserved memory region.
+    pub fn device_scopes(&self) -> &[DeviceScope] {
+        &self.device_scopes
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod)]
 pub struct RmrrHeader {
@@ -219,6 +233,25 @@ impl_from_bytes!(
 );
 
 impl DeviceScope {
+    /// The type of a PCI endpoint device.
+    const TYPE_PCI_ENDPOINT: u8 = 1;
+
+    /// Returns true if the device scope indicates a PCI endpoint device.
+    pub fn is_pci_endpoint(&self) -> bool {
+        self.header.typ == Self::TYPE_PCI_ENDPOINT
+    }
+
+    /// Returns the number of the bus where the path starts.
+    pub fn start_bus_number(&self) -> u8 {
+        self.header.start_bus_number
+    }
+
+    /// Returns the path from the start bus to the device, as a list of (device, function)
+    /// pairs. All the pairs except the last one represent PCI-to-PCI bridges.
+    pub fn path(&self) -> &[(u8, u8)] {
+        &self.path
+    }
+
     /// Parses a [`DeviceScope`] from a prefix of the bytes.
     ///
     /// # Panics
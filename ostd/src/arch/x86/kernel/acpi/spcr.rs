@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use acpi::{
+    sdt::{SdtHeader, Signature},
+    AcpiTable,
+};
+
+/// Serial Port Console Redirection Table (SPCR).
+///
+/// The table describes the serial port that the firmware uses as the console.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/drivers/serports/serial-port-console-redirection-table>
+#[derive(Debug, Clone, Copy)]
+pub struct Spcr {
+    /// The type of the UART interface.
+    pub interface_type: u8,
+    /// The address space of the registers, where 0 is the system memory and 1 is the system I/O.
+    pub address_space: u8,
+    /// The width of the registers in bits.
+    pub bit_width: u8,
+    /// The address of the first register.
+    pub address: u64,
+    /// The interrupt types supported by the UART.
+    pub interrupt_type: u8,
+    /// The PC-AT-compatible IRQ, which is valid if the UART supports the dual-8259 interrupt.
+    pub irq: u8,
+    /// The baud rate that the firmware has configured, where 0 means "as is".
+    pub baud_rate: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct SpcrHeader {
+    header: SdtHeader,
+}
+
+// SAFETY: The `SpcrHeader` is the header for the SPCR structure. All its fields are described in
+// the ACPI specification.
+unsafe impl AcpiTable for SpcrHeader {
+    const SIGNATURE: Signature = Signature::SPCR;
+    fn header(&self) -> &acpi::sdt::SdtHeader {
+        &self.header
+    }
+}
+
+/// The length of the SPCR table of revision 1 and 2.
+const SPCR_MIN_LENGTH: usize = 80;
+
+impl Spcr {
+    /// Creates a instance from ACPI table.
+    pub fn new() -> Option<Self> {
+        let acpi_table = super::get_acpi_tables()?;
+
+        let spcr_mapping = acpi_table.find_table::<SpcrHeader>().ok()?;
+        let length = spcr_mapping.header.length as usize;
+        if length < SPCR_MIN_LENGTH {
+            return None;
+        }
+        // SAFETY: `find_table` returns a region of memory that belongs to the ACPI table. This
+        // memory region is valid to read, properly initialized, lives for `'static`, and will
+        // never be mutated.
+        let slice = unsafe {
+            core::slice::from_raw_parts(
+                spcr_mapping
+                    .virtual_start()
+                    .as_ptr()
+                    .cast::<u8>()
+                    .cast_const(),
+                spcr_mapping.mapped_length(),
+            )
+        };
+
+        // The base address is a Generic Address Structure (GAS) at offset 40:
+        // { address_space_id: u8, bit_width: u8, bit_offset: u8, access_size: u8, address: u64 }
+        Some(Self {
+            interface_type: slice[36],
+            address_space: slice[40],
+            bit_width: slice[41],
+            address: u64::from_le_bytes(slice[44..52].try_into().unwrap()),
+            interrupt_type: slice[52],
+            irq: slice[53],
+            baud_rate: slice[58],
+        })
+    }
+}
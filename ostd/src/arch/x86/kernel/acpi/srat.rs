@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use acpi::{
+    sdt::{SdtHeader, Signature},
+    AcpiTable,
+};
+
+use crate::mm::Paddr;
+
+/// System Resource Affinity Table (SRAT).
+///
+/// The table associates the processors and the memory ranges with the
+/// proximity domains, i.e., the NUMA nodes.
+#[derive(Debug)]
+pub struct Srat {
+    cpu_affinities: Vec<CpuAffinity>,
+    memory_affinities: Vec<MemoryAffinity>,
+}
+
+/// The proximity domain of a processor.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuAffinity {
+    /// The (x2)APIC ID of the processor.
+    pub apic_id: u32,
+    pub proximity_domain: u32,
+}
+
+/// The proximity domain of a memory range.
+#[derive(Debug, Clone)]
+pub struct MemoryAffinity {
+    pub range: Range<Paddr>,
+    pub proximity_domain: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct SratHeader {
+    header: SdtHeader,
+    reserved1: u32,
+    reserved2: u64,
+}
+
+// SAFETY: The `SratHeader` is the header for the SRAT structure. All its fields are described in
+// the ACPI specification.
+unsafe impl AcpiTable for SratHeader {
+    const SIGNATURE: Signature = Signature::SRAT;
+    fn header(&self) -> &acpi::sdt::SdtHeader {
+        &self.header
+    }
+}
+
+const LOCAL_APIC_AFFINITY: u8 = 0;
+const MEMORY_AFFINITY: u8 = 1;
+const X2APIC_AFFINITY: u8 = 2;
+
+/// The flag indicating that an affinity structure is enabled.
+const AFFINITY_ENABLED: u32 = 1;
+
+impl Srat {
+    /// Creates a instance from ACPI table.
+    pub fn new() -> Option<Self> {
+        let acpi_table = super::get_acpi_tables()?;
+
+        let srat_mapping = acpi_table.find_table::<SratHeader>().ok()?;
+        let length = srat_mapping.header.length as usize;
+        // SAFETY: `find_table` returns a region of memory that belongs to the ACPI table. This
+        // memory region is valid to read, properly initialized, lives for `'static`, and will
+        // never be mutated.
+        let slice = unsafe {
+            core::slice::from_raw_parts(
+                srat_mapping
+                    .virtual_start()
+                    .as_ptr()
+                    .cast::<u8>()
+                    .cast_const(),
+                srat_mapping.mapped_length(),
+            )
+        };
+
+        let read_u32 = |bytes: &[u8], offset: usize| {
+            u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+        };
+
+        let mut cpu_affinities = Vec::new();
+        let mut memory_affinities = Vec::new();
+        let mut index = core::mem::size_of::<SratHeader>();
+        while index + 2 <= length {
+            // CommonHeader { type: u8, length: u8 }
+            let typ = slice[index];
+            let entry_length = slice[index + 1] as usize;
+            if entry_length < 2 || index + entry_length > length {
+                break;
+            }
+            let bytes = &slice[index..index + entry_length];
+
+            match typ {
+                LOCAL_APIC_AFFINITY if entry_length >= 16 => {
+                    if read_u32(bytes, 4) & AFFINITY_ENABLED != 0 {
+                        let proximity_domain =
+                            u32::from_le_bytes([bytes[2], bytes[9], bytes[10], bytes[11]]);
+                        cpu_affinities.push(CpuAffinity {
+                            apic_id: bytes[3] as u32,
+                            proximity_domain,
+                        });
+                    }
+                }
+                MEMORY_AFFINITY if entry_length >= 40 => {
+                    if read_u32(bytes, 28) & AFFINITY_ENABLED != 0 {
+                        let base =
+                            read_u32(bytes, 8) as usize | (read_u32(bytes, 12) as usize) << 32;
+                        let size =
+                            read_u32(bytes, 16) as usize | (read_u32(bytes, 20) as usize) << 32;
+                        memory_affinities.push(MemoryAffinity {
+                            range: base..base + size,
+                            proximity_domain: read_u32(bytes, 2),
+                        });
+                    }
+                }
+                X2APIC_AFFINITY if entry_length >= 24 => {
+                    if read_u32(bytes, 12) & AFFINITY_ENABLED != 0 {
+                        cpu_affinities.push(CpuAffinity {
+                            apic_id: read_u32(bytes, 8),
+                            proximity_domain: read_u32(bytes, 4),
+                        });
+                    }
+                }
+                // Other affinity structures (e.g., GICC and generic initiators) are ignored.
+                _ => {}
+            }
+
+            index += entry_length;
+        }
+
+        Some(Srat {
+            cpu_affinities,
+            memory_affinities,
+        })
+    }
+
+    pub fn cpu_affinities(&self) -> &[CpuAffinity] {
+        &self.cpu_affinities
+    }
+
+    pub fn memory_affinities(&self) -> &[MemoryAffinity] {
+        &self.memory_affinities
+    }
+}
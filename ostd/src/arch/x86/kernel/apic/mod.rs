@@ -114,6 +114,12 @@ pub trait Apic: ApicTimer {
 
     /// Send a general inter-processor interrupt.
     unsafe fn send_ipi(&self, icr: Icr);
+
+    /// Sets the performance monitoring counter (PMC) register in the APIC.
+    /// Bit 0-7:   The interrupt vector of performance monitoring interrupt.
+    /// Bit 8-10:  Delivery Mode, 0 for Fixed, 4 for NMI.
+    /// Bit 16:    Mask bit.
+    fn set_lvt_pmc(&self, value: u32);
 }
 
 pub trait ApicTimer {
@@ -313,12 +319,11 @@ pub enum DeliveryMode {
     /// avoided by BIOS and operating system software.
     #[expect(dead_code)]
     LowestPriority = 0b001,
-    /// Non-Maskable Interrupt
+    /// System Management Interrupt
     #[expect(dead_code)]
     Smi = 0b010,
     _Reserved = 0b011,
-    /// System Management Interrupt
-    #[expect(dead_code)]
+    /// Non-Maskable Interrupt
     Nmi = 0b100,
     /// Delivers an INIT request to the target processor or processors, which causes them to
     /// perform an initialization.
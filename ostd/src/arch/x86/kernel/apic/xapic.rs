@@ -91,6 +91,10 @@ impl super::Apic for XApic {
             }
         }
     }
+
+    fn set_lvt_pmc(&self, value: u32) {
+        self.write(xapic::XAPIC_LVT_PMI, value);
+    }
 }
 
 impl ApicTimer for XApic {
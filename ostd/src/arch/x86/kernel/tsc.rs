@@ -12,6 +12,7 @@ use x86::cpuid::cpuid;
 
 use crate::{
     arch::timer::{
+        hpet,
         pit::{self, OperatingMode},
         TIMER_FREQ,
     },
@@ -22,12 +23,23 @@ use crate::{
 pub(in crate::arch) static TSC_FREQ: AtomicU64 = AtomicU64::new(0);
 
 pub fn init_tsc_freq() {
-    let tsc_freq =
-        determine_tsc_freq_via_cpuid().map_or_else(determine_tsc_freq_via_pit, |freq| freq);
+    let tsc_freq = determine_tsc_freq_via_cpuid()
+        .or_else(determine_tsc_freq_via_hpet)
+        .unwrap_or_else(determine_tsc_freq_via_pit);
     TSC_FREQ.store(tsc_freq, Ordering::Relaxed);
     info!("TSC frequency:{:?} Hz", tsc_freq);
 }
 
+/// Returns whether the TSC is invariant according to CPUID.
+pub fn is_tsc_invariant() -> bool {
+    // CPUID 0x8000_0007: Advanced Power Management Information Leaf
+    let max_extended_cpuid = cpuid!(0x8000_0000).eax;
+    if max_extended_cpuid < 0x8000_0007 {
+        return false;
+    }
+    cpuid!(0x8000_0007).edx & (1 << 8) != 0
+}
+
 /// Determines TSC frequency via CPUID. If the CPU does not support calculating TSC frequency by
 /// CPUID, the function will return None. The unit of the return value is KHz.
 ///
@@ -68,8 +80,18 @@ pub fn determine_tsc_freq_via_cpuid() -> Option<u64> {
     }
 }
 
-/// When kernel cannot get the TSC frequency from CPUID, it can leverage
-/// the PIT to calculate this frequency.
+/// Determines TSC frequency by calibrating it against the HPET. If there is no
+/// HPET, the function will return None.
+pub fn determine_tsc_freq_via_hpet() -> Option<u64> {
+    // SAFETY: Reading the TSC has no side effects.
+    hpet::calibrate(|| unsafe { _rdtsc() })
+}
+
+/// When kernel cannot get the TSC frequency from CPUID or the HPET, it can
+/// leverage the PIT to calculate this frequency.
+///
+/// The PIT interrupts are delivered with a variable latency, so this is less
+/// precise than the calibration with the HPET.
 pub fn determine_tsc_freq_via_pit() -> u64 {
     // Allocate IRQ
     let mut irq = IrqLine::alloc().unwrap();
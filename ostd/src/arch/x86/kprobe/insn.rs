@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A minimal x86-64 instruction decoder for kprobes.
+//!
+//! Kprobes need to know the length of the probed instruction so that it can be
+//! copied to an out-of-line slot, and how the instruction affects the
+//! instruction pointer so that the execution context can be fixed up after the
+//! instruction is single-stepped at a different address.
+//!
+//! The decoder only understands the legacy (non-VEX/EVEX) encodings that are
+//! commonly emitted in kernel code. Instructions that it cannot decode, or
+//! that cannot be single-stepped out of line safely, are rejected.
+
+/// The maximum length of an x86 instruction.
+pub(super) const MAX_INSN_LEN: usize = 15;
+
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Insn {
+    /// The length of the instruction in bytes.
+    pub(super) len: usize,
+    /// The offset of the 32-bit RIP-relative displacement, if any.
+    pub(super) rip_rel_disp_offset: Option<usize>,
+    /// How the instruction changes the control flow.
+    pub(super) kind: InsnKind,
+}
+
+/// The control-flow behavior of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum InsnKind {
+    /// The instruction falls through, or jumps relative to its own address.
+    ///
+    /// The instruction pointer after single-stepping must be rebased from the
+    /// out-of-line slot to the original address.
+    Relative,
+    /// A relative call. The pushed return address must also be rebased.
+    RelativeCall,
+    /// An absolute jump or a return.
+    ///
+    /// The instruction pointer after single-stepping is already correct.
+    Absolute,
+    /// An absolute (indirect) call. The pushed return address must be rebased.
+    AbsoluteCall,
+    /// `pushf`. The trap flag must be cleared from the pushed flags.
+    Pushf,
+}
+
+/// Errors returned by the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum DecodeError {
+    /// The bytes are not a complete, known instruction.
+    Unknown,
+    /// The instruction is known but cannot be probed.
+    Unsupported,
+}
+
+#[derive(Clone, Copy)]
+enum Imm {
+    None,
+    /// An 8-bit immediate.
+    Byte,
+    /// A 16-bit immediate.
+    Word,
+    /// A 16-bit or 32-bit immediate, depending on the operand-size prefix.
+    Z,
+    /// A 16-bit, 32-bit or 64-bit immediate (`mov r, imm`).
+    V,
+    /// An address-sized memory offset (`mov al, moffs`).
+    Moffs,
+    /// `enter imm16, imm8`.
+    Enter,
+}
+
+/// Decodes the instruction at the beginning of `bytes`.
+pub(super) fn decode(bytes: &[u8]) -> Result<Insn, DecodeError> {
+    let byte_at = |i: usize| bytes.get(i).copied().ok_or(DecodeError::Unknown);
+
+    let mut pos = 0;
+    let mut operand_size_16 = false;
+    let mut address_size_32 = false;
+    let mut rex_w = false;
+
+    // Legacy prefixes.
+    loop {
+        match byte_at(pos)? {
+            0x66 => operand_size_16 = true,
+            0x67 => address_size_32 = true,
+            0xf0 | 0xf2 | 0xf3 | 0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 => {}
+            _ => break,
+        }
+        pos += 1;
+    }
+
+    // The REX prefix must immediately precede the opcode.
+    let rex = byte_at(pos)?;
+    if (0x40..=0x4f).contains(&rex) {
+        rex_w = rex & 0x08 != 0;
+        pos += 1;
+    }
+
+    let opcode = byte_at(pos)?;
+    pos += 1;
+
+    let (has_modrm, mut imm, mut kind) = if opcode == 0x0f {
+        let opcode2 = byte_at(pos)?;
+        pos += 1;
+        match opcode2 {
+            // Three-byte opcodes.
+            0x38 => {
+                pos += 1;
+                (true, Imm::None, InsnKind::Relative)
+            }
+            0x3a => {
+                pos += 1;
+                (true, Imm::Byte, InsnKind::Relative)
+            }
+            // `syscall`, `sysret`, `ud2`, `sysenter`, `sysexit`, `rsm` and 3DNow!.
+            0x05 | 0x07 | 0x0b | 0x0f | 0x34 | 0x35 | 0xaa => {
+                return Err(DecodeError::Unsupported);
+            }
+            0x06
+            | 0x08
+            | 0x09
+            | 0x0e
+            | 0x30..=0x33
+            | 0x37
+            | 0x77
+            | 0xa0..=0xa2
+            | 0xa8
+            | 0xa9
+            | 0xc8..=0xcf => (false, Imm::None, InsnKind::Relative),
+            // `jcc rel32`.
+            0x80..=0x8f => (false, Imm::Z, InsnKind::Relative),
+            0x70..=0x73 | 0xa4 | 0xac | 0xba | 0xc2 | 0xc4..=0xc6 => {
+                (true, Imm::Byte, InsnKind::Relative)
+            }
+            _ => (true, Imm::None, InsnKind::Relative),
+        }
+    } else {
+        match opcode {
+            // Invalid in 64-bit mode, VEX/EVEX prefixes, far transfers,
+            // interrupts, `iret`, `hlt`, `popf`, `cli` and `sti`.
+            0x06
+            | 0x07
+            | 0x0e
+            | 0x16
+            | 0x17
+            | 0x1e
+            | 0x1f
+            | 0x27
+            | 0x2f
+            | 0x37
+            | 0x3f
+            | 0x60..=0x62
+            | 0x82
+            | 0x9a
+            | 0x9d
+            | 0xc4
+            | 0xc5
+            | 0xca..=0xcf
+            | 0xd4..=0xd6
+            | 0xea
+            | 0xf1
+            | 0xf4
+            | 0xfa
+            | 0xfb => return Err(DecodeError::Unsupported),
+            0x00..=0x3f => match opcode & 0x07 {
+                0x00..=0x03 => (true, Imm::None, InsnKind::Relative),
+                0x04 => (false, Imm::Byte, InsnKind::Relative),
+                0x05 => (false, Imm::Z, InsnKind::Relative),
+                _ => return Err(DecodeError::Unknown),
+            },
+            0x50..=0x5f
+            | 0x6c..=0x6f
+            | 0x90..=0x9b
+            | 0x9e
+            | 0x9f
+            | 0xa4..=0xa7
+            | 0xaa..=0xaf
+            | 0xc9
+            | 0xd7
+            | 0xec..=0xef
+            | 0xf5
+            | 0xf8
+            | 0xf9
+            | 0xfc
+            | 0xfd => (false, Imm::None, InsnKind::Relative),
+            0x9c => (false, Imm::None, InsnKind::Pushf),
+            0x63 | 0x84..=0x8f | 0xd0..=0xd3 | 0xd8..=0xdf | 0xfe | 0xff => {
+                (true, Imm::None, InsnKind::Relative)
+            }
+            0x69 | 0x81 | 0xc7 => (true, Imm::Z, InsnKind::Relative),
+            0x6b | 0x80 | 0x83 | 0xc0 | 0xc1 | 0xc6 => (true, Imm::Byte, InsnKind::Relative),
+            0x68 => (false, Imm::Z, InsnKind::Relative),
+            0x6a | 0xa8 | 0xb0..=0xb7 | 0xe4..=0xe7 => (false, Imm::Byte, InsnKind::Relative),
+            0xa9 => (false, Imm::Z, InsnKind::Relative),
+            0xa0..=0xa3 => (false, Imm::Moffs, InsnKind::Relative),
+            0xb8..=0xbf => (false, Imm::V, InsnKind::Relative),
+            // `jcc rel8`, `loop*` and `jrcxz`.
+            0x70..=0x7f | 0xe0..=0xe3 | 0xeb => (false, Imm::Byte, InsnKind::Relative),
+            0xe9 => (false, Imm::Z, InsnKind::Relative),
+            0xe8 => (false, Imm::Z, InsnKind::RelativeCall),
+            0xc2 => (false, Imm::Word, InsnKind::Absolute),
+            0xc3 => (false, Imm::None, InsnKind::Absolute),
+            0xc8 => (false, Imm::Enter, InsnKind::Relative),
+            0xf6 | 0xf7 => (true, Imm::None, InsnKind::Relative),
+            // `int3` and the prefixes that were handled above.
+            _ => return Err(DecodeError::Unsupported),
+        }
+    };
+
+    let mut rip_rel_disp_offset = None;
+    if has_modrm {
+        let modrm = byte_at(pos)?;
+        pos += 1;
+        let mode = modrm >> 6;
+        let reg = (modrm >> 3) & 0x07;
+        let rm = modrm & 0x07;
+
+        match opcode {
+            // `test r/m, imm` in group 3.
+            0xf6 if reg <= 1 => imm = Imm::Byte,
+            0xf7 if reg <= 1 => imm = Imm::Z,
+            0xff => match reg {
+                2 => kind = InsnKind::AbsoluteCall,
+                4 => kind = InsnKind::Absolute,
+                // Far calls and far jumps.
+                3 | 5 => return Err(DecodeError::Unsupported),
+                7 => return Err(DecodeError::Unknown),
+                _ => {}
+            },
+            _ => {}
+        }
+
+        if mode != 0b11 {
+            if rm == 0b100 {
+                let sib = byte_at(pos)?;
+                pos += 1;
+                if mode == 0b00 && sib & 0x07 == 0b101 {
+                    pos += 4;
+                }
+            } else if mode == 0b00 && rm == 0b101 {
+                rip_rel_disp_offset = Some(pos);
+                pos += 4;
+            }
+            match mode {
+                0b01 => pos += 1,
+                0b10 => pos += 4,
+                _ => {}
+            }
+        }
+    }
+
+    pos += match imm {
+        Imm::None => 0,
+        Imm::Byte => 1,
+        Imm::Word => 2,
+        Imm::Z if operand_size_16 => 2,
+        Imm::Z => 4,
+        Imm::V if rex_w => 8,
+        Imm::V if operand_size_16 => 2,
+        Imm::V => 4,
+        Imm::Moffs if address_size_32 => 4,
+        Imm::Moffs => 8,
+        Imm::Enter => 3,
+    };
+
+    if pos > MAX_INSN_LEN || pos > bytes.len() {
+        return Err(DecodeError::Unknown);
+    }
+
+    Ok(Insn {
+        len: pos,
+        rip_rel_disp_offset,
+        kind,
+    })
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn decode_len(bytes: &[u8]) -> usize {
+        decode(bytes).unwrap().len
+    }
+
+    #[ktest]
+    fn common_instructions() {
+        // push rbp
+        assert_eq!(decode_len(&[0x55]), 1);
+        // mov rbp, rsp
+        assert_eq!(decode_len(&[0x48, 0x89, 0xe5]), 3);
+        // sub rsp, 0x20
+        assert_eq!(decode_len(&[0x48, 0x83, 0xec, 0x20]), 4);
+        // mov rax, qword ptr [rsp + 0x8]
+        assert_eq!(decode_len(&[0x48, 0x8b, 0x44, 0x24, 0x08]), 5);
+        // movabs rax, 0x1122334455667788
+        assert_eq!(
+            decode_len(&[0x48, 0xb8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]),
+            10
+        );
+        // nop dword ptr [rax + rax]
+        assert_eq!(decode_len(&[0x0f, 0x1f, 0x44, 0x00, 0x00]), 5);
+        // test byte ptr [rdi], 0x1
+        assert_eq!(decode_len(&[0xf6, 0x07, 0x01]), 3);
+    }
+
+    #[ktest]
+    fn control_flow() {
+        // call rel32
+        let insn = decode(&[0xe8, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(insn.kind, InsnKind::RelativeCall);
+        // ret
+        assert_eq!(decode(&[0xc3]).unwrap().kind, InsnKind::Absolute);
+        // call qword ptr [rax]
+        assert_eq!(decode(&[0xff, 0x10]).unwrap().kind, InsnKind::AbsoluteCall);
+        // jne rel32
+        assert_eq!(decode_len(&[0x0f, 0x85, 0x10, 0x00, 0x00, 0x00]), 6);
+    }
+
+    #[ktest]
+    fn rip_relative() {
+        // lea rax, [rip + 0x100]
+        let insn = decode(&[0x48, 0x8d, 0x05, 0x00, 0x01, 0x00, 0x00]).unwrap();
+        assert_eq!(insn.len, 7);
+        assert_eq!(insn.rip_rel_disp_offset, Some(3));
+    }
+
+    #[ktest]
+    fn rejected_instructions() {
+        assert_eq!(decode(&[0xcc]), Err(DecodeError::Unsupported));
+        assert_eq!(decode(&[0x0f, 0x05]), Err(DecodeError::Unsupported));
+        assert_eq!(decode(&[0xfa]), Err(DecodeError::Unsupported));
+        assert_eq!(decode(&[0x48, 0x8b]), Err(DecodeError::Unknown));
+    }
+}
@@ -0,0 +1,482 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel probes (kprobes).
+//!
+//! A kprobe can be attached to almost any instruction in the kernel text at
+//! runtime. It is implemented as follows:
+//!
+//! 1. When a kprobe is registered, the probed instruction is decoded and
+//!    copied to an out-of-line slot, and its first byte is replaced with an
+//!    `int3` instruction.
+//! 2. When the CPU hits the `int3`, the pre-handler of the kprobe is called.
+//!    Then the CPU is redirected to the out-of-line slot with the trap flag
+//!    set, so it executes the original instruction and traps again.
+//! 3. On the single-step trap, the execution context is fixed up as if the
+//!    original instruction had been executed in place, and the post-handler
+//!    of the kprobe is called.
+//!
+//! The code that handles the traps can never be probed. It is placed in the
+//! `.text.kprobes` section, which is blacklisted along with the out-of-line
+//! slots. More regions can be blacklisted with [`add_to_blacklist`].
+
+mod insn;
+
+use alloc::collections::BTreeMap;
+use core::{
+    arch::asm,
+    cell::{RefCell, SyncUnsafeCell},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use id_alloc::IdAlloc;
+use insn::{Insn, InsnKind, MAX_INSN_LEN};
+use spin::Once;
+use x86_64::registers::rflags::RFlags;
+
+use crate::{
+    cpu::CpuSet,
+    cpu_local,
+    prelude::*,
+    sync::{LocalIrqDisabled, SpinLock},
+    trap::{self, TrapFrame},
+    Error,
+};
+
+/// The handler of a kprobe.
+///
+/// The handler is called with local IRQs disabled. It can inspect and modify
+/// the registers of the probed context through the trap frame.
+pub type KprobeHandler = dyn Fn(&mut TrapFrame) + Send + Sync + 'static;
+
+const INT3: u8 = 0xcc;
+
+/// The size of an out-of-line slot.
+const SLOT_SIZE: usize = 16;
+/// The number of out-of-line slots, which limits the number of live kprobes.
+const NR_SLOTS: usize = 256;
+
+/// The out-of-line slots to single-step the probed instructions.
+///
+/// The slots reside in the kernel text, so that they are executable and the
+/// RIP-relative displacements of the copied instructions can be fixed up
+/// (the kernel text is smaller than 2 GiB).
+#[link_section = ".text.kprobes.insn_slots"]
+static INSN_SLOTS: SyncUnsafeCell<[u8; SLOT_SIZE * NR_SLOTS]> =
+    SyncUnsafeCell::new([INT3; SLOT_SIZE * NR_SLOTS]);
+
+static SLOT_ALLOCATOR: Once<SpinLock<IdAlloc, LocalIrqDisabled>> = Once::new();
+
+/// All the registered kprobes, indexed by the probed addresses.
+static KPROBES: SpinLock<BTreeMap<Vaddr, Arc<KprobeInner>>, LocalIrqDisabled> =
+    SpinLock::new(BTreeMap::new());
+
+/// The address ranges that cannot be probed, except for `.text.kprobes`.
+static BLACKLIST: SpinLock<Vec<Range<Vaddr>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+cpu_local! {
+    static KPROBE_CTL: RefCell<KprobeCtl> = RefCell::new(KprobeCtl::new());
+}
+
+// These symbols are provided by the linker script.
+extern "C" {
+    fn __stext();
+    fn __etext();
+    fn __kprobes_text_start();
+    fn __kprobes_text_end();
+}
+
+/// Options to register a kprobe.
+pub struct KprobeOptions {
+    addr: Vaddr,
+    pre_handler: Option<Box<KprobeHandler>>,
+    post_handler: Option<Box<KprobeHandler>>,
+}
+
+impl KprobeOptions {
+    /// Creates the options to probe the instruction at `addr`.
+    pub fn new(addr: Vaddr) -> Self {
+        Self {
+            addr,
+            pre_handler: None,
+            post_handler: None,
+        }
+    }
+
+    /// Sets the handler that is called before the probed instruction is executed.
+    ///
+    /// When the handler is called, the instruction pointer in the trap frame is
+    /// the probed address. If the handler changes the instruction pointer, the
+    /// execution is redirected to the new address without executing the probed
+    /// instruction, and the post-handler is not called.
+    pub fn pre_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut TrapFrame) + Send + Sync + 'static,
+    {
+        self.pre_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler that is called after the probed instruction is executed.
+    pub fn post_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut TrapFrame) + Send + Sync + 'static,
+    {
+        self.post_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Registers the kprobe and arms it.
+    ///
+    /// This method fails with [`Error::InvalidArgs`] if the address is not in
+    /// the kernel text, is blacklisted, is already probed, or if the probed
+    /// instruction cannot be single-stepped out of line. It fails with
+    /// [`Error::NotEnoughResources`] if all the out-of-line slots are in use.
+    pub fn register(self) -> Result<Kprobe> {
+        let addr = self.addr;
+        if !is_kernel_text(addr) || is_blacklisted(addr) {
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut kprobes = KPROBES.lock();
+        if kprobes.contains_key(&addr) {
+            return Err(Error::InvalidArgs);
+        }
+
+        // SAFETY: The address is in the kernel text, which is always mapped.
+        // No kprobe is armed at the address, so the text is not being modified.
+        let orig_bytes = unsafe { core::ptr::read_volatile(addr as *const [u8; MAX_INSN_LEN]) };
+        let insn = insn::decode(&orig_bytes).map_err(|_| Error::InvalidArgs)?;
+
+        let slot = alloc_slot().ok_or(Error::NotEnoughResources)?;
+        let inner = Arc::new(KprobeInner {
+            addr,
+            orig_byte: orig_bytes[0],
+            insn,
+            slot,
+            pre_handler: self.pre_handler,
+            post_handler: self.post_handler,
+            nr_hits: AtomicUsize::new(0),
+            nr_missed: AtomicUsize::new(0),
+        });
+        inner.prepare_slot(&orig_bytes)?;
+
+        kprobes.insert(addr, inner.clone());
+        // SAFETY: The kprobe is registered, so the `int3` will be handled.
+        unsafe { poke_text(addr, INT3) };
+        drop(kprobes);
+
+        Ok(Kprobe { inner })
+    }
+}
+
+/// A registered kprobe.
+///
+/// The kprobe is disarmed and unregistered when it is dropped.
+#[must_use]
+pub struct Kprobe {
+    inner: Arc<KprobeInner>,
+}
+
+impl Kprobe {
+    /// Returns the probed address.
+    pub fn addr(&self) -> Vaddr {
+        self.inner.addr
+    }
+
+    /// Returns the number of times the kprobe has been hit.
+    pub fn nr_hits(&self) -> usize {
+        self.inner.nr_hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times the handlers are skipped.
+    ///
+    /// The handlers are skipped if the kprobe is hit while the handlers of
+    /// another kprobe are running on the same CPU.
+    pub fn nr_missed(&self) -> usize {
+        self.inner.nr_missed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Kprobe {
+    fn drop(&mut self) {
+        let mut kprobes = KPROBES.lock();
+        // SAFETY: Restoring the original byte disarms the kprobe. A CPU that
+        // has already trapped on the `int3` will find the kprobe missing and
+        // re-execute the original instruction.
+        unsafe { poke_text(self.inner.addr, self.inner.orig_byte) };
+        kprobes.remove(&self.inner.addr);
+        // The out-of-line slot is freed when the last CPU that is
+        // single-stepping in it drops its reference to `KprobeInner`.
+    }
+}
+
+/// Adds an address range to the kprobe blacklist.
+///
+/// Kprobes cannot be registered in blacklisted ranges. Existing kprobes in the
+/// range are not affected.
+pub fn add_to_blacklist(range: Range<Vaddr>) {
+    BLACKLIST.lock().push(range);
+}
+
+struct KprobeInner {
+    addr: Vaddr,
+    orig_byte: u8,
+    insn: Insn,
+    slot: usize,
+    pre_handler: Option<Box<KprobeHandler>>,
+    post_handler: Option<Box<KprobeHandler>>,
+    nr_hits: AtomicUsize,
+    nr_missed: AtomicUsize,
+}
+
+impl KprobeInner {
+    fn slot_addr(&self) -> Vaddr {
+        slot_base() + self.slot * SLOT_SIZE
+    }
+
+    /// Copies the probed instruction to the out-of-line slot.
+    fn prepare_slot(&self, orig_bytes: &[u8; MAX_INSN_LEN]) -> Result<()> {
+        let mut slot_bytes = [INT3; SLOT_SIZE];
+        slot_bytes[..self.insn.len].copy_from_slice(&orig_bytes[..self.insn.len]);
+
+        // Rebase the RIP-relative displacement to the slot address.
+        if let Some(offset) = self.insn.rip_rel_disp_offset {
+            let disp = i32::from_le_bytes(slot_bytes[offset..offset + 4].try_into().unwrap());
+            let new_disp = (disp as isize)
+                .checked_add(self.addr as isize - self.slot_addr() as isize)
+                .and_then(|disp| i32::try_from(disp).ok())
+                .ok_or(Error::InvalidArgs)?;
+            slot_bytes[offset..offset + 4].copy_from_slice(&new_disp.to_le_bytes());
+        }
+
+        // SAFETY: The slot is allocated to this kprobe and no CPU can execute
+        // it before the kprobe is armed.
+        unsafe {
+            core::ptr::write_volatile(self.slot_addr() as *mut [u8; SLOT_SIZE], slot_bytes);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for KprobeInner {
+    fn drop(&mut self) {
+        SLOT_ALLOCATOR.get().unwrap().lock().free(self.slot);
+    }
+}
+
+/// The per-CPU kprobe control block.
+struct KprobeCtl {
+    /// The kprobe whose handlers are running or which is being single-stepped.
+    current: Option<ActiveKprobe>,
+    /// The kprobe hit while running the handlers of `current`.
+    reentered: Option<ActiveKprobe>,
+}
+
+struct ActiveKprobe {
+    kprobe: Arc<KprobeInner>,
+    was_irq_enabled: bool,
+    is_stepping: bool,
+}
+
+impl KprobeCtl {
+    const fn new() -> Self {
+        Self {
+            current: None,
+            reentered: None,
+        }
+    }
+}
+
+/// Handles a breakpoint exception from the kernel mode.
+///
+/// Returns `true` if the exception is caused by a kprobe.
+#[link_section = ".text.kprobes"]
+pub(super) fn handle_breakpoint(f: &mut TrapFrame) -> bool {
+    let addr = f.rip - 1;
+    let kprobe = KPROBES.lock().get(&addr).cloned();
+    let Some(kprobe) = kprobe else {
+        // SAFETY: The address is readable if it is in the kernel text.
+        if is_kernel_text(addr) && unsafe { core::ptr::read_volatile(addr as *const u8) } != INT3 {
+            // The kprobe was unregistered after the CPU trapped. Re-execute
+            // the original instruction.
+            f.rip = addr;
+            return true;
+        }
+        return false;
+    };
+
+    let irq_guard = trap::disable_local();
+    let ctl = KPROBE_CTL.get_with(&irq_guard);
+    let was_irq_enabled = f.rflags & RFlags::INTERRUPT_FLAG.bits() as usize != 0;
+
+    if ctl.borrow().current.is_some() {
+        // The kprobe is hit by the handlers of another kprobe. Skip the
+        // handlers to avoid infinite recursions.
+        let mut ctl = ctl.borrow_mut();
+        assert!(
+            ctl.reentered.is_none(),
+            "kprobe: unrecoverable reentrance at {:#x}",
+            addr
+        );
+        kprobe.nr_missed.fetch_add(1, Ordering::Relaxed);
+        setup_single_step(f, &kprobe);
+        ctl.reentered = Some(ActiveKprobe {
+            kprobe,
+            was_irq_enabled,
+            is_stepping: true,
+        });
+        return true;
+    }
+
+    kprobe.nr_hits.fetch_add(1, Ordering::Relaxed);
+    ctl.borrow_mut().current = Some(ActiveKprobe {
+        kprobe: kprobe.clone(),
+        was_irq_enabled,
+        is_stepping: false,
+    });
+
+    // The `RefCell` must not be borrowed while running the handler, which may
+    // hit other kprobes.
+    f.rip = addr;
+    if let Some(pre_handler) = kprobe.pre_handler.as_ref() {
+        pre_handler(f);
+    }
+
+    if f.rip != addr {
+        // The pre-handler has redirected the execution.
+        ctl.borrow_mut().current = None;
+        return true;
+    }
+
+    setup_single_step(f, &kprobe);
+    ctl.borrow_mut().current.as_mut().unwrap().is_stepping = true;
+    true
+}
+
+/// Handles a debug exception from the kernel mode.
+///
+/// Returns `true` if the exception is the single-step trap of a kprobe.
+#[link_section = ".text.kprobes"]
+pub(super) fn handle_debug(f: &mut TrapFrame) -> bool {
+    let irq_guard = trap::disable_local();
+    let ctl = KPROBE_CTL.get_with(&irq_guard);
+
+    let reentered = ctl.borrow_mut().reentered.take();
+    if let Some(active) = reentered {
+        finish_single_step(f, &active);
+        return true;
+    }
+
+    let active = {
+        let mut ctl = ctl.borrow_mut();
+        if !ctl
+            .current
+            .as_ref()
+            .is_some_and(|active| active.is_stepping)
+        {
+            return false;
+        }
+        ctl.current.take().unwrap()
+    };
+    finish_single_step(f, &active);
+
+    // Other kprobes can be handled normally in the post-handler, as the
+    // control block has been cleared.
+    if let Some(post_handler) = active.kprobe.post_handler.as_ref() {
+        post_handler(f);
+    }
+    true
+}
+
+/// Redirects the trapped context to single-step the out-of-line slot.
+#[link_section = ".text.kprobes"]
+fn setup_single_step(f: &mut TrapFrame, kprobe: &KprobeInner) {
+    f.rip = kprobe.slot_addr();
+    f.rflags |= RFlags::TRAP_FLAG.bits() as usize;
+    // Interrupts must not be handled in the out-of-line slot.
+    f.rflags &= !(RFlags::INTERRUPT_FLAG.bits() as usize);
+}
+
+/// Fixes up the trapped context as if the probed instruction had been
+/// executed at its original address.
+#[link_section = ".text.kprobes"]
+fn finish_single_step(f: &mut TrapFrame, active: &ActiveKprobe) {
+    let kprobe = &active.kprobe;
+    let slot_addr = kprobe.slot_addr();
+    let next_insn_addr = kprobe.addr + kprobe.insn.len;
+
+    // The CPU pushes the stack pointer of the interrupted context right after
+    // the last field of the trap frame.
+    let sp_addr = f as *const TrapFrame as usize + size_of::<TrapFrame>();
+    // SAFETY: `sp_addr` points to the exception stack frame pushed by the CPU.
+    let sp = unsafe { core::ptr::read(sp_addr as *const usize) };
+
+    match kprobe.insn.kind {
+        InsnKind::Relative => f.rip = f.rip - slot_addr + kprobe.addr,
+        InsnKind::RelativeCall => {
+            f.rip = f.rip - slot_addr + kprobe.addr;
+            // SAFETY: The return address was just pushed onto the stack.
+            unsafe { core::ptr::write(sp as *mut usize, next_insn_addr) };
+        }
+        InsnKind::Absolute => {}
+        InsnKind::AbsoluteCall => {
+            // SAFETY: The return address was just pushed onto the stack.
+            unsafe { core::ptr::write(sp as *mut usize, next_insn_addr) };
+        }
+        InsnKind::Pushf => {
+            f.rip = f.rip - slot_addr + kprobe.addr;
+            // SAFETY: The flags were just pushed onto the stack.
+            let pushed_flags = unsafe { &mut *(sp as *mut usize) };
+            *pushed_flags &= !(RFlags::TRAP_FLAG.bits() as usize);
+            if active.was_irq_enabled {
+                *pushed_flags |= RFlags::INTERRUPT_FLAG.bits() as usize;
+            }
+        }
+    }
+
+    f.rflags &= !(RFlags::TRAP_FLAG.bits() as usize);
+    if active.was_irq_enabled {
+        f.rflags |= RFlags::INTERRUPT_FLAG.bits() as usize;
+    }
+
+    // The single-step bit in DR6 is sticky.
+    // SAFETY: Clearing DR6 has no side effects other than clearing the status.
+    unsafe { asm!("mov dr6, {}", in(reg) 0usize, options(nomem, nostack)) };
+}
+
+/// Writes a byte to the kernel text and synchronizes all CPUs.
+///
+/// # Safety
+///
+/// The caller must ensure that the modified instruction is valid when it is
+/// executed by any CPU.
+unsafe fn poke_text(addr: Vaddr, byte: u8) {
+    // The kernel text is mapped writable (see `init_kernel_page_table`).
+    // SAFETY: The caller guarantees the validity of the modified text.
+    unsafe { core::ptr::write_volatile(addr as *mut u8, byte) };
+
+    // Returning from the IPI executes `iretq`, which serializes the
+    // instruction stream on the remote CPUs.
+    crate::smp::inter_processor_call(&CpuSet::new_full(), || {});
+}
+
+fn alloc_slot() -> Option<usize> {
+    SLOT_ALLOCATOR
+        .call_once(|| SpinLock::new(IdAlloc::with_capacity(NR_SLOTS)))
+        .lock()
+        .alloc()
+}
+
+fn slot_base() -> Vaddr {
+    INSN_SLOTS.get() as Vaddr
+}
+
+fn is_kernel_text(addr: Vaddr) -> bool {
+    (__stext as Vaddr..__etext as Vaddr).contains(&addr)
+}
+
+fn is_blacklisted(addr: Vaddr) -> bool {
+    (__kprobes_text_start as Vaddr..__kprobes_text_end as Vaddr).contains(&addr)
+        || BLACKLIST.lock().iter().any(|range| range.contains(&addr))
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The paravirtualized interfaces of KVM guests.
+//!
+//! Reference: <https://docs.kernel.org/virt/kvm/x86/hypercalls.html>
+
+use core::arch::{asm, x86_64::__cpuid};
+
+use spin::Once;
+
+use crate::{
+    mm::{Frame, FrameAllocOptions, VmIo},
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+/// The signature in the hypervisor CPUID leaf of KVM.
+const KVM_SIGNATURE: [u8; 12] = *b"KVMKVMKVM\0\0\0";
+const KVM_CPUID_SIGNATURE: u32 = 0x4000_0000;
+
+const KVM_HC_CLOCK_PAIRING: u64 = 9;
+/// Pairs the TSC with the wall-clock time (i.e., `CLOCK_REALTIME`) of the host.
+const KVM_CLOCK_PAIRING_WALLCLOCK: u64 = 0;
+
+/// A pair of the wall-clock time of the host and the TSC of the guest at the same instant.
+#[derive(Debug, Clone, Copy)]
+pub struct KvmClockPairing {
+    /// The seconds of the wall-clock time of the host.
+    pub sec: i64,
+    /// The nanoseconds of the wall-clock time of the host.
+    pub nsec: i64,
+    /// The TSC of the guest.
+    pub tsc: u64,
+}
+
+/// The frame where the host writes the results of `KVM_HC_CLOCK_PAIRING`.
+static CLOCK_PAIRING_FRAME: Once<SpinLock<Frame<()>, LocalIrqDisabled>> = Once::new();
+
+/// Returns whether the kernel runs as a KVM guest.
+pub fn is_kvm_guest() -> bool {
+    // SAFETY: The CPUID instruction is always available on x86-64.
+    let features = unsafe { __cpuid(1) };
+    // CPUID.01H:ECX[31] indicates the presence of a hypervisor.
+    if features.ecx & (1 << 31) == 0 {
+        return false;
+    }
+
+    // SAFETY: The hypervisor CPUID leaves are available with a hypervisor.
+    let leaf = unsafe { __cpuid(KVM_CPUID_SIGNATURE) };
+    let mut signature = [0u8; 12];
+    signature[0..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&leaf.ecx.to_le_bytes());
+    signature[8..12].copy_from_slice(&leaf.edx.to_le_bytes());
+    signature == KVM_SIGNATURE
+}
+
+/// Reads the wall-clock time of the host with the TSC of the guest at the same instant.
+///
+/// Returns `None` if the kernel does not run as a KVM guest or the host does not support it,
+/// e.g., if the host does not use the TSC as its clocksource.
+pub fn clock_pairing() -> Option<KvmClockPairing> {
+    if !is_kvm_guest() {
+        return None;
+    }
+
+    let frame = CLOCK_PAIRING_FRAME
+        .call_once(|| SpinLock::new(FrameAllocOptions::new().alloc_frame().unwrap()))
+        .lock();
+
+    let ret: i64;
+    // SAFETY: The hypercall only writes the results to the frame, which is owned by us. `rbx` is
+    // reserved by LLVM, so it is saved and restored around the hypercall.
+    unsafe {
+        asm!(
+            "xchg {paddr}, rbx",
+            "vmcall",
+            "xchg {paddr}, rbx",
+            paddr = inout(reg) frame.start_paddr() as u64 => _,
+            inout("rax") KVM_HC_CLOCK_PAIRING => ret,
+            in("rcx") KVM_CLOCK_PAIRING_WALLCLOCK,
+            options(nostack),
+        );
+    }
+    if ret != 0 {
+        return None;
+    }
+
+    Some(KvmClockPairing {
+        sec: frame.read_val(0).unwrap(),
+        nsec: frame.read_val(8).unwrap(),
+        tsc: frame.read_val(16).unwrap(),
+    })
+}
@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Live patching of kernel functions.
+//!
+//! A live patch replaces a set of kernel functions with new implementations
+//! without rebooting. The calls to a patched function are redirected to its
+//! replacement by a [kprobe](super::kprobe) at the entry of the function.
+//!
+//! # Consistency model
+//!
+//! Switching all the tasks to the new functions at once is unsafe, since a
+//! task may be sleeping in the middle of an old function and may expect the
+//! other old functions to be called later. So the tasks are migrated one by
+//! one, at _safe points_ where no patched function can be on their stacks:
+//!
+//! - when the task is about to return to the user space;
+//! - when the task calls [`migrate_current`] explicitly, which is intended
+//!   for kernel threads that never return to the user space.
+//!
+//! Each patch operation (applying or reverting a patch) starts a
+//! _transition_. During a transition, the tasks that have not been migrated
+//! keep seeing the functions as they were before the transition. The
+//! transition completes once all the tasks have been migrated, and only one
+//! transition can be in progress at a time. A stalled transition can be
+//! completed forcibly with [`force_transition`], at the risk of the
+//! inconsistency described above.
+//!
+//! # Stacking
+//!
+//! Multiple patches can replace the same function. The calls are redirected
+//! to the replacement in the most recently applied patch. Patches can only be
+//! reverted in the reverse order of being applied.
+
+use alloc::{collections::BTreeMap, string::String};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::kprobe::{Kprobe, KprobeOptions};
+use crate::{
+    prelude::*,
+    sync::{LocalIrqDisabled, Mutex, SpinLock},
+    task::Task,
+    trap::TrapFrame,
+    Error,
+};
+
+/// The generation that the tasks are migrating to.
+///
+/// The generation is increased by one whenever a transition starts.
+static TARGET_GEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The generation that all the tasks are forcibly considered to be in, at least.
+static FORCED_GEN: AtomicUsize = AtomicUsize::new(0);
+
+static TASK_COUNTERS: SpinLock<TaskCounters, LocalIrqDisabled> = SpinLock::new(TaskCounters {
+    nr_tasks: 0,
+    nr_pending: 0,
+});
+
+/// The applied patches, in the order of being applied.
+static PATCHES: Mutex<Vec<Arc<PatchInner>>> = Mutex::new(Vec::new());
+
+/// The patched functions, indexed by their addresses.
+static PATCHED_FUNCS: Mutex<BTreeMap<Vaddr, PatchedFunc>> = Mutex::new(BTreeMap::new());
+
+struct TaskCounters {
+    /// The number of live tasks.
+    nr_tasks: usize,
+    /// The number of live tasks that have not been migrated to the target generation.
+    nr_pending: usize,
+}
+
+/// Options to apply a live patch.
+pub struct PatchOptions {
+    name: String,
+    funcs: Vec<(Vaddr, Vaddr)>,
+}
+
+impl PatchOptions {
+    /// Creates the options of a patch with the given name.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            funcs: Vec::new(),
+        }
+    }
+
+    /// Replaces the function at `old_func` with the function at `new_func`.
+    ///
+    /// The two functions must have the same signature.
+    pub fn replace(mut self, old_func: Vaddr, new_func: Vaddr) -> Self {
+        self.funcs.push((old_func, new_func));
+        self
+    }
+
+    /// Applies the patch and starts the transition to it.
+    ///
+    /// This method fails with [`Error::NotEnoughResources`] if another
+    /// transition is in progress, and with [`Error::InvalidArgs`] if any
+    /// function cannot be replaced (see [`KprobeOptions::register`]).
+    pub fn apply(self) -> Result<Patch> {
+        let mut patches = PATCHES.lock();
+        if is_transition_pending() {
+            return Err(Error::NotEnoughResources);
+        }
+        let mut patched_funcs = PATCHED_FUNCS.lock();
+        collect_reverted(&mut patches, &mut patched_funcs);
+
+        let inner = Arc::new(PatchInner {
+            name: self.name,
+            funcs: self.funcs,
+            enabled_gen: TARGET_GEN.load(Ordering::Acquire) + 1,
+            disabled_gen: AtomicUsize::new(usize::MAX),
+        });
+
+        // Arming the kprobes does not change the functions seen by any task,
+        // since no task is in the new generation yet.
+        for (i, &(old_func, _)) in inner.funcs.iter().enumerate() {
+            if !patched_funcs.contains_key(&old_func) {
+                match PatchedFunc::new(old_func) {
+                    Ok(func) => {
+                        patched_funcs.insert(old_func, func);
+                    }
+                    Err(err) => {
+                        for &(old_func, _) in &inner.funcs[..i] {
+                            remove_versions(&mut patched_funcs, old_func, &inner);
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+            patched_funcs[&old_func].versions.lock().push(inner.clone());
+        }
+
+        patches.push(inner.clone());
+        start_transition();
+
+        Ok(Patch { inner })
+    }
+}
+
+/// An applied live patch.
+///
+/// Dropping a `Patch` does not revert it.
+pub struct Patch {
+    inner: Arc<PatchInner>,
+}
+
+impl Patch {
+    /// Returns the name of the patch.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Returns whether the patch has been reverted.
+    pub fn is_reverted(&self) -> bool {
+        self.inner.disabled_gen.load(Ordering::Acquire) != usize::MAX
+    }
+
+    /// Reverts the patch and starts the transition back to the replaced functions.
+    ///
+    /// This method fails with [`Error::NotEnoughResources`] if another
+    /// transition is in progress, and with [`Error::InvalidArgs`] if the patch
+    /// is not the most recently applied patch that is still in effect.
+    pub fn revert(&self) -> Result<()> {
+        let patches = PATCHES.lock();
+        if is_transition_pending() {
+            return Err(Error::NotEnoughResources);
+        }
+
+        let top = patches
+            .iter()
+            .rev()
+            .find(|patch| patch.disabled_gen.load(Ordering::Acquire) == usize::MAX);
+        if !top.is_some_and(|top| Arc::ptr_eq(top, &self.inner)) {
+            return Err(Error::InvalidArgs);
+        }
+
+        self.inner
+            .disabled_gen
+            .store(TARGET_GEN.load(Ordering::Acquire) + 1, Ordering::Release);
+        start_transition();
+
+        Ok(())
+    }
+}
+
+/// Returns whether a transition is in progress.
+pub fn is_transition_pending() -> bool {
+    TASK_COUNTERS.lock().nr_pending != 0
+}
+
+/// Completes the transition in progress by migrating all the tasks at once.
+///
+/// This should only be used when the transition stalls, e.g., because some
+/// kernel thread never reaches a safe point. The tasks that are in the middle
+/// of a patched function may observe a mix of the old and new functions.
+pub fn force_transition() {
+    let mut counters = TASK_COUNTERS.lock();
+    FORCED_GEN.store(TARGET_GEN.load(Ordering::Acquire), Ordering::Release);
+    counters.nr_pending = 0;
+}
+
+/// Migrates the current task to the generation of the transition in progress.
+///
+/// The caller must ensure that no patched function is on the stack of the
+/// current task, which is the case when a kernel thread is at the outermost
+/// level of its main loop.
+pub fn migrate_current() {
+    let Some(current) = Task::current() else {
+        return;
+    };
+    let state = current.patch_state();
+    if state.gen() == TARGET_GEN.load(Ordering::Acquire) {
+        return;
+    }
+
+    let mut counters = TASK_COUNTERS.lock();
+    let target_gen = TARGET_GEN.load(Ordering::Acquire);
+    if state.gen() != target_gen {
+        state.gen.store(target_gen, Ordering::Release);
+        counters.nr_pending -= 1;
+    }
+}
+
+/// The live patching state of a task.
+#[derive(Debug)]
+pub(crate) struct TaskPatchState {
+    gen: AtomicUsize,
+}
+
+impl TaskPatchState {
+    /// Creates the state of a new task.
+    ///
+    /// A new task has no function on its stack, so it starts in the target
+    /// generation.
+    pub(crate) fn new() -> Self {
+        let mut counters = TASK_COUNTERS.lock();
+        counters.nr_tasks += 1;
+        Self {
+            gen: AtomicUsize::new(TARGET_GEN.load(Ordering::Acquire)),
+        }
+    }
+
+    /// Returns the generation that the task is in.
+    fn gen(&self) -> usize {
+        self.gen
+            .load(Ordering::Acquire)
+            .max(FORCED_GEN.load(Ordering::Acquire))
+    }
+}
+
+impl Drop for TaskPatchState {
+    fn drop(&mut self) {
+        let mut counters = TASK_COUNTERS.lock();
+        counters.nr_tasks -= 1;
+        if self.gen() != TARGET_GEN.load(Ordering::Acquire) {
+            counters.nr_pending -= 1;
+        }
+    }
+}
+
+struct PatchInner {
+    name: String,
+    funcs: Vec<(Vaddr, Vaddr)>,
+    /// The generation since which the patch is in effect.
+    enabled_gen: usize,
+    /// The generation since which the patch is reverted, or `usize::MAX`.
+    disabled_gen: AtomicUsize,
+}
+
+impl PatchInner {
+    fn is_visible_to(&self, gen: usize) -> bool {
+        self.enabled_gen <= gen && gen < self.disabled_gen.load(Ordering::Acquire)
+    }
+
+    fn new_func_of(&self, old_func: Vaddr) -> Option<Vaddr> {
+        self.funcs
+            .iter()
+            .find(|(old, _)| *old == old_func)
+            .map(|(_, new)| *new)
+    }
+}
+
+/// A function replaced by one or more patches.
+struct PatchedFunc {
+    _kprobe: Kprobe,
+    /// The patches that replace the function, in the order of being applied.
+    versions: Arc<SpinLock<Vec<Arc<PatchInner>>, LocalIrqDisabled>>,
+}
+
+impl PatchedFunc {
+    fn new(addr: Vaddr) -> Result<Self> {
+        let versions: Arc<SpinLock<Vec<Arc<PatchInner>>, LocalIrqDisabled>> =
+            Arc::new(SpinLock::new(Vec::new()));
+
+        let versions_cloned = versions.clone();
+        let kprobe = KprobeOptions::new(addr)
+            .pre_handler(move |f: &mut TrapFrame| {
+                let gen = current_gen();
+                let versions = versions_cloned.lock();
+                let new_func = versions
+                    .iter()
+                    .rev()
+                    .find(|patch| patch.is_visible_to(gen))
+                    .and_then(|patch| patch.new_func_of(addr));
+                if let Some(new_func) = new_func {
+                    // The kprobe is at the entry of the function, so jumping to
+                    // the replacement is equivalent to calling it.
+                    f.rip = new_func;
+                }
+            })
+            .register()?;
+
+        Ok(Self {
+            _kprobe: kprobe,
+            versions,
+        })
+    }
+}
+
+/// Returns the generation of the functions seen by the current context.
+fn current_gen() -> usize {
+    match Task::current() {
+        Some(current) => current.patch_state().gen(),
+        None => TARGET_GEN.load(Ordering::Acquire),
+    }
+}
+
+fn start_transition() {
+    let mut counters = TASK_COUNTERS.lock();
+    TARGET_GEN.fetch_add(1, Ordering::AcqRel);
+    counters.nr_pending = counters.nr_tasks;
+}
+
+/// Removes the patches that have been reverted and are seen by no task.
+///
+/// This must be called when no transition is in progress.
+fn collect_reverted(
+    patches: &mut Vec<Arc<PatchInner>>,
+    patched_funcs: &mut BTreeMap<Vaddr, PatchedFunc>,
+) {
+    patches.retain(|patch| {
+        if patch.disabled_gen.load(Ordering::Acquire) == usize::MAX {
+            return true;
+        }
+        for &(old_func, _) in &patch.funcs {
+            remove_versions(patched_funcs, old_func, patch);
+        }
+        false
+    });
+}
+
+/// Removes the version of a function in a patch, and disarms the kprobe of
+/// the function if no version is left.
+fn remove_versions(
+    patched_funcs: &mut BTreeMap<Vaddr, PatchedFunc>,
+    old_func: Vaddr,
+    patch: &Arc<PatchInner>,
+) {
+    let Some(func) = patched_funcs.get(&old_func) else {
+        return;
+    };
+
+    let mut versions = func.versions.lock();
+    versions.retain(|version| !Arc::ptr_eq(version, patch));
+    let is_empty = versions.is_empty();
+    drop(versions);
+
+    if is_empty {
+        patched_funcs.remove(&old_func);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::hint::black_box;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[inline(never)]
+    fn old_func(x: usize) -> usize {
+        black_box(x) + 1
+    }
+
+    #[inline(never)]
+    fn new_func(x: usize) -> usize {
+        black_box(x) + 2
+    }
+
+    #[inline(never)]
+    fn newer_func(x: usize) -> usize {
+        black_box(x) + 3
+    }
+
+    /// Calls `old_func` via a pointer, so the call cannot be inlined or folded.
+    fn call_old_func() -> usize {
+        black_box(old_func as fn(usize) -> usize)(1)
+    }
+
+    #[ktest]
+    fn apply_and_revert() {
+        let patch = PatchOptions::new("apply_and_revert")
+            .replace(old_func as Vaddr, new_func as Vaddr)
+            .apply()
+            .unwrap();
+        assert_eq!(patch.name(), "apply_and_revert");
+
+        // The current task keeps seeing the old function until it is migrated.
+        assert!(is_transition_pending());
+        assert_eq!(call_old_func(), 2);
+        assert_eq!(
+            PatchOptions::new("pending").apply().unwrap_err(),
+            Error::NotEnoughResources
+        );
+        migrate_current();
+        assert_eq!(call_old_func(), 3);
+        force_transition();
+        assert!(!is_transition_pending());
+
+        patch.revert().unwrap();
+        assert!(patch.is_reverted());
+        assert_eq!(call_old_func(), 3);
+        migrate_current();
+        assert_eq!(call_old_func(), 2);
+        force_transition();
+    }
+
+    #[ktest]
+    fn stacked_patches() {
+        let patch = PatchOptions::new("stacked_patches")
+            .replace(old_func as Vaddr, new_func as Vaddr)
+            .apply()
+            .unwrap();
+        force_transition();
+        let newer_patch = PatchOptions::new("newer_stacked_patches")
+            .replace(old_func as Vaddr, newer_func as Vaddr)
+            .apply()
+            .unwrap();
+        force_transition();
+        assert_eq!(call_old_func(), 4);
+
+        // The patches can only be reverted in the reverse order of being applied.
+        assert_eq!(patch.revert().unwrap_err(), Error::InvalidArgs);
+        newer_patch.revert().unwrap();
+        force_transition();
+        assert_eq!(call_old_func(), 3);
+        assert_eq!(newer_patch.revert().unwrap_err(), Error::InvalidArgs);
+
+        patch.revert().unwrap();
+        force_transition();
+        assert_eq!(call_old_func(), 2);
+    }
+}
@@ -10,8 +10,12 @@ pub(crate) mod io;
 pub mod iommu;
 pub(crate) mod irq;
 pub(crate) mod kernel;
+pub mod kprobe;
+pub mod kvm;
+pub mod livepatch;
 pub(crate) mod mm;
 pub(crate) mod pci;
+pub mod pmu;
 pub mod qemu;
 pub mod serial;
 pub mod task;
@@ -26,7 +30,8 @@ use x86::cpuid::{CpuId, FeatureInfo};
 pub(crate) mod tdx_guest;
 
 use core::{
-    arch::x86_64::{_rdrand64_step, _rdtsc},
+    arch::x86_64::{_rdrand64_step, _rdseed64_step, _rdtsc},
+    hint::spin_loop,
     sync::atomic::Ordering,
 };
 
@@ -55,6 +60,20 @@ pub(crate) fn init_cvm_guest() {
 }
 
 static CPU_FEATURES: Once<FeatureInfo> = Once::new();
+static HAS_RDSEED: Once<bool> = Once::new();
+
+/// Initializes the NUMA topology from the SRAT.
+fn init_numa() {
+    let Some(srat) = kernel::acpi::srat::Srat::new() else {
+        return;
+    };
+
+    let cpu_affinities = (srat.cpu_affinities().iter())
+        .map(|affinity| (affinity.apic_id, affinity.proximity_domain));
+    let memory_affinities = (srat.memory_affinities().iter())
+        .map(|affinity| (affinity.range.clone(), affinity.proximity_domain));
+    crate::numa::init(cpu_affinities, memory_affinities);
+}
 
 /// Architecture-specific initialization on the bootstrapping processor.
 ///
@@ -70,6 +89,9 @@ pub(crate) unsafe fn late_init_on_bsp() {
     irq::init();
 
     kernel::acpi::init();
+    init_numa();
+    // SAFETY: The GDT has been initialized and we're in the boot context.
+    unsafe { trap::gdt::update_cpunode() };
 
     let io_mem_builder = construct_io_mem_allocator_builder();
 
@@ -83,8 +105,16 @@ pub(crate) unsafe fn late_init_on_bsp() {
         }
     }
 
+    if_tdx_enabled!({
+    } else {
+        if let Err(err) = timer::hpet::init(&io_mem_builder) {
+            warn!("HPET initialization error:{:?}", err);
+        }
+    });
+
     kernel::tsc::init_tsc_freq();
     timer::init_bsp();
+    pmu::init();
 
     // SAFETY: We're on the BSP and we're ready to boot all APs.
     unsafe { crate::boot::smp::boot_all_aps() };
@@ -105,6 +135,8 @@ pub(crate) unsafe fn late_init_on_bsp() {
     // 2. All the port I/O regions belonging to the system device are defined using the macros.
     // 3. `MAX_IO_PORT` defined in `crate::arch::io` is the maximum value specified by x86-64.
     unsafe { crate::io::init(io_mem_builder) };
+
+    timer::pm_timer::init();
 }
 
 /// Architecture-specific initialization on the application processor.
@@ -130,6 +162,15 @@ pub fn tsc_freq() -> u64 {
     kernel::tsc::TSC_FREQ.load(Ordering::Acquire)
 }
 
+/// Returns whether the TSC is invariant.
+///
+/// An invariant TSC runs at a constant rate in all ACPI P-, C-, and T-states.
+/// It is still possible that the TSC becomes unreliable (e.g., on some
+/// virtual machines), so the TSC should be checked against other clocksources.
+pub fn is_tsc_invariant() -> bool {
+    kernel::tsc::is_tsc_invariant()
+}
+
 /// Reads the current value of the processor’s time-stamp counter (TSC).
 pub fn read_tsc() -> u64 {
     // SAFETY: It is safe to read a time-related counter.
@@ -138,13 +179,21 @@ pub fn read_tsc() -> u64 {
 
 /// Reads a hardware generated 64-bit random value.
 ///
-/// Returns None if no random value was generated.
+/// Returns None if no random value was generated or the CPU does not support
+/// `RDRAND`.
 pub fn read_random() -> Option<u64> {
     // Recommendation from "Intel® Digital Random Number Generator (DRNG) Software
     // Implementation Guide" - Section 5.2.1 and "Intel® 64 and IA-32 Architectures
     // Software Developer’s Manual" - Volume 1 - Section 7.3.17.1.
     const RETRY_LIMIT: usize = 10;
 
+    if !CPU_FEATURES
+        .get()
+        .is_some_and(|features| features.has_rdrand())
+    {
+        return None;
+    }
+
     for _ in 0..RETRY_LIMIT {
         let mut val = 0;
         let generated = unsafe { _rdrand64_step(&mut val) };
@@ -155,6 +204,35 @@ pub fn read_random() -> Option<u64> {
     None
 }
 
+/// Reads a hardware generated 64-bit random seed.
+///
+/// Unlike [`read_random`], which returns the output of a hardware pseudorandom
+/// number generator, this returns the output of the hardware entropy source,
+/// so the value is suitable for seeding other random number generators.
+///
+/// Returns None if no random seed was generated or the CPU does not support
+/// `RDSEED`.
+pub fn read_random_seed() -> Option<u64> {
+    // `RDSEED` may fail more often than `RDRAND` if the entropy source is
+    // drained, so we spin a bit between retries.
+    const RETRY_LIMIT: usize = 100;
+
+    if !HAS_RDSEED.get().is_some_and(|has_rdseed| *has_rdseed) {
+        return None;
+    }
+
+    for _ in 0..RETRY_LIMIT {
+        let mut val = 0;
+        // SAFETY: `RDSEED` is supported as checked above.
+        let generated = unsafe { _rdseed64_step(&mut val) };
+        if generated == 1 {
+            return Some(val);
+        }
+        spin_loop();
+    }
+    None
+}
+
 fn has_avx() -> bool {
     use core::arch::x86_64::{__cpuid, __cpuid_count};
 
@@ -183,6 +261,20 @@ fn has_avx512() -> bool {
     cpuid_result.ebx & (1 << 16) != 0
 }
 
+fn has_rdseed() -> bool {
+    use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+    let cpuid_result = unsafe { __cpuid(0) };
+    if cpuid_result.eax < 7 {
+        // CPUID function 7 is not supported
+        return false;
+    }
+
+    let cpuid_result = unsafe { __cpuid_count(7, 0) };
+    // Check for RDSEED (bit 18 of ebx)
+    cpuid_result.ebx & (1 << 18) != 0
+}
+
 pub(crate) fn enable_cpu_features() {
     use x86_64::registers::{control::Cr4Flags, model_specific::EferFlags, xcontrol::XCr0Flags};
 
@@ -190,6 +282,7 @@ pub(crate) fn enable_cpu_features() {
         let cpuid = CpuId::new();
         cpuid.get_feature_info().unwrap()
     });
+    HAS_RDSEED.call_once(has_rdseed);
 
     cpu::context::enable_essential_features();
 
@@ -2,8 +2,12 @@
 
 //! PCI bus access
 
-use super::device::io_port::{ReadWriteAccess, WriteOnlyAccess};
-use crate::{bus::pci::PciDeviceLocation, io::IoPort, prelude::*, trap::IrqLine};
+use super::{
+    device::io_port::{ReadWriteAccess, WriteOnlyAccess},
+    iommu::invalidate_irt_cache,
+    irq::IrqLine,
+};
+use crate::{bus::pci::PciDeviceLocation, cpu::CpuId, io::IoPort, prelude::*};
 
 static PCI_ADDRESS_PORT: IoPort<u32, WriteOnlyAccess> = unsafe { IoPort::new(0x0CF8) };
 static PCI_DATA_PORT: IoPort<u32, ReadWriteAccess> = unsafe { IoPort::new(0x0CFC) };
@@ -27,12 +31,30 @@ pub(crate) fn has_pci_bus() -> bool {
 
 pub(crate) const MSIX_DEFAULT_MSG_ADDR: u32 = 0xFEE0_0000;
 
-pub(crate) fn construct_remappable_msix_address(irq: &IrqLine) -> u32 {
-    let mut handle = irq.inner_irq().bind_remapping_entry().unwrap().lock();
+/// Constructs the message address that delivers an MSI-X interrupt to the CPU.
+///
+/// Returns `None` if the CPU cannot be addressed without interrupt remapping.
+pub(crate) fn construct_msix_address(cpu: CpuId) -> Option<u32> {
+    // Like `send_ipi`, the CPU ID is used as the APIC ID. Only 8 bits of the destination ID are
+    // available in the message address.
+    let apic_id = u8::try_from(cpu.as_usize()).ok()?;
 
-    // Enable irt entry
+    // The destination ID is on address[19:12].
+    Some(MSIX_DEFAULT_MSG_ADDR | ((apic_id as u32) << 12))
+}
+
+/// Constructs the message address that delivers an MSI-X interrupt to the CPU through the
+/// interrupt remapping table.
+///
+/// The remapping table entry of the IRQ line is updated to target the CPU.
+pub(crate) fn construct_remappable_msix_address(irq: &IrqLine, cpu: CpuId) -> u32 {
+    let mut handle = irq.bind_remapping_entry().unwrap().lock();
+
+    // Enable irt entry. Like `send_ipi`, the CPU ID is used as the APIC ID.
     let irt_entry_mut = handle.irt_entry_mut().unwrap();
-    irt_entry_mut.enable_default(irq.num() as u32);
+    irt_entry_mut.enable_default(irq.num() as u32, cpu.as_usize() as u32);
+    // The entry may have been cached with the old destination.
+    invalidate_irt_cache();
 
     // Use remappable format. The bits[4:3] should be always set to 1 according to the manual.
     let mut address = MSIX_DEFAULT_MSG_ADDR | 0b1_1000;
@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The performance monitoring unit (PMU).
+//!
+//! This module supports the architectural performance monitoring of Intel
+//! processors (see Intel SDM Vol. 3B, Chapter 21 "Performance Monitoring").
+//! A general-purpose counter can be programmed to count an event and raise a
+//! performance monitoring interrupt (PMI) when it overflows. We program the
+//! local APIC to deliver PMIs as NMIs, so sampling works even when local IRQs
+//! are disabled. See [`SamplingCounter`] for details.
+//!
+//! TODO: Support the performance counters of AMD processors.
+
+use core::{
+    arch::x86_64::__cpuid_count,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use spin::Once;
+use x86::msr::{rdmsr, wrmsr};
+
+use super::{
+    kernel::apic,
+    trap::nmi::{self, NmiHandlerHandle, NmiStatus},
+};
+use crate::{
+    cpu::{current_cpu_racy, CpuId, PinCurrentCpu},
+    cpu_local,
+    trap::TrapFrame,
+    Error, Result,
+};
+
+const IA32_PMC0: u32 = 0xc1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+const IA32_PERF_CAPABILITIES: u32 = 0x345;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38f;
+const IA32_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+/// The alias of `IA32_PMC0` that supports full-width writes.
+const IA32_A_PMC0: u32 = 0x4c1;
+
+const PERFEVTSEL_USR: u64 = 1 << 16;
+const PERFEVTSEL_OS: u64 = 1 << 17;
+const PERFEVTSEL_INT: u64 = 1 << 20;
+const PERFEVTSEL_EN: u64 = 1 << 22;
+
+/// Delivers PMIs as NMIs. The vector is ignored.
+const LVT_PMC_NMI: u32 = 0b100 << 8;
+
+/// The maximum number of general-purpose counters that we support.
+const MAX_NR_COUNTERS: usize = 8;
+
+/// The information about the PMU.
+#[derive(Debug)]
+pub struct PmuInfo {
+    version: u8,
+    nr_counters: u8,
+    counter_width: u8,
+    /// The bit vector of unavailable architectural events (CPUID.0AH:EBX).
+    unavailable_events: u32,
+    has_full_width_write: bool,
+}
+
+impl PmuInfo {
+    /// Returns the version of the architectural performance monitoring.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the number of general-purpose counters per CPU.
+    pub fn nr_counters(&self) -> usize {
+        self.nr_counters as usize
+    }
+
+    /// Returns the bit width of general-purpose counters.
+    pub fn counter_width(&self) -> u8 {
+        self.counter_width
+    }
+
+    /// Returns whether `event` can be counted.
+    pub fn is_event_available(&self, event: PmuEvent) -> bool {
+        self.unavailable_events & (1 << event as u32) == 0
+    }
+
+    /// Returns the maximum sampling period.
+    ///
+    /// Without full-width writes, only the lower 32 bits of a counter can be
+    /// written and are sign-extended, so the period is limited to 31 bits.
+    pub fn max_period(&self) -> u64 {
+        if self.has_full_width_write {
+            (1 << (self.counter_width - 1)) - 1
+        } else {
+            (1 << 31) - 1
+        }
+    }
+
+    fn counter_mask(&self) -> u64 {
+        (1 << self.counter_width) - 1
+    }
+}
+
+static PMU_INFO: Once<PmuInfo> = Once::new();
+
+/// Returns the information about the PMU, or `None` if there is no usable PMU.
+pub fn info() -> Option<&'static PmuInfo> {
+    PMU_INFO.get()
+}
+
+/// The architectural performance events.
+///
+/// The values are the bit indices in CPUID.0AH:EBX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PmuEvent {
+    /// Unhalted core cycles.
+    CoreCycles = 0,
+    /// Retired instructions.
+    Instructions = 1,
+    /// Unhalted reference cycles.
+    ReferenceCycles = 2,
+    /// Last-level cache references.
+    LlcReferences = 3,
+    /// Last-level cache misses.
+    LlcMisses = 4,
+    /// Retired branch instructions.
+    BranchInstructions = 5,
+    /// Retired mispredicted branch instructions.
+    BranchMisses = 6,
+}
+
+impl PmuEvent {
+    /// Returns the event select and the unit mask of the event.
+    fn select(self) -> (u8, u8) {
+        match self {
+            Self::CoreCycles => (0x3c, 0x00),
+            Self::Instructions => (0xc0, 0x00),
+            Self::ReferenceCycles => (0x3c, 0x01),
+            Self::LlcReferences => (0x2e, 0x4f),
+            Self::LlcMisses => (0x2e, 0x41),
+            Self::BranchInstructions => (0xc4, 0x00),
+            Self::BranchMisses => (0xc5, 0x00),
+        }
+    }
+}
+
+/// The handler of counter overflows.
+///
+/// The handler is called in the NMI context, so it has the same restrictions
+/// as NMI handlers (see [`crate::arch::trap::nmi`]).
+pub type OverflowHandler = fn(&TrapFrame);
+
+/// The per-CPU state of a general-purpose counter.
+struct CounterSlot {
+    /// The overflow handler, stored as a function pointer, or zero if the
+    /// counter is free.
+    handler: AtomicUsize,
+    period: AtomicU64,
+}
+
+impl CounterSlot {
+    const fn new() -> Self {
+        Self {
+            handler: AtomicUsize::new(0),
+            period: AtomicU64::new(0),
+        }
+    }
+}
+
+cpu_local! {
+    static COUNTERS: [CounterSlot; MAX_NR_COUNTERS] = [const { CounterSlot::new() }; MAX_NR_COUNTERS];
+}
+
+static NMI_HANDLER: Once<NmiHandlerHandle> = Once::new();
+
+/// Initializes the PMU.
+pub(super) fn init() {
+    // SAFETY: The CPUID leaf 0 is always available.
+    let max_leaf = unsafe { __cpuid_count(0, 0) }.eax;
+    if max_leaf < 0xa {
+        return;
+    }
+
+    // SAFETY: The CPUID leaf 0xa is available as checked above.
+    let leaf = unsafe { __cpuid_count(0xa, 0) };
+    let version = (leaf.eax & 0xff) as u8;
+    let nr_counters = ((leaf.eax >> 8) & 0xff) as u8;
+    let counter_width = ((leaf.eax >> 16) & 0xff) as u8;
+    let nr_events = (leaf.eax >> 24) & 0xff;
+    if version == 0 || nr_counters == 0 || counter_width < 32 {
+        return;
+    }
+
+    // The events beyond the length of EBX are unavailable.
+    let unavailable_events = leaf.ebx | u32::MAX.checked_shl(nr_events).unwrap_or(0);
+
+    // SAFETY: The CPUID leaf 1 is always available.
+    let has_pdcm = unsafe { __cpuid_count(1, 0) }.ecx & (1 << 15) != 0;
+    let has_full_width_write = has_pdcm && {
+        // SAFETY: `IA32_PERF_CAPABILITIES` is available if PDCM is supported.
+        let capabilities = unsafe { rdmsr(IA32_PERF_CAPABILITIES) };
+        capabilities & (1 << 13) != 0
+    };
+
+    let info = PMU_INFO.call_once(|| PmuInfo {
+        version,
+        nr_counters: nr_counters.min(MAX_NR_COUNTERS as u8),
+        counter_width,
+        unavailable_events,
+        has_full_width_write,
+    });
+    log::info!("[PMU] {:?}", info);
+
+    match nmi::register_handler(handle_nmi) {
+        Ok(handle) => {
+            NMI_HANDLER.call_once(|| handle);
+        }
+        Err(err) => log::warn!("[PMU] failed to register the NMI handler: {:?}", err),
+    }
+}
+
+/// A general-purpose counter that samples an event on a CPU.
+///
+/// Every time the event occurs `period` times, the counter overflows and the
+/// overflow handler is called in the NMI context on the CPU.
+///
+/// The counter is stopped when the object is dropped, which must happen on the
+/// same CPU.
+#[derive(Debug)]
+pub struct SamplingCounter {
+    cpu: CpuId,
+    index: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl SamplingCounter {
+    /// Starts sampling `event` on the current CPU.
+    ///
+    /// This method fails with [`Error::InvalidArgs`] if there is no usable PMU,
+    /// the event is unavailable, or the period is zero. The period is capped at
+    /// [`PmuInfo::max_period`]. It fails with [`Error::NotEnoughResources`] if
+    /// all the counters are in use.
+    pub fn start(
+        event: PmuEvent,
+        period: u64,
+        handler: OverflowHandler,
+        guard: &dyn PinCurrentCpu,
+    ) -> Result<Self> {
+        let Some(info) = info() else {
+            return Err(Error::InvalidArgs);
+        };
+        if !info.is_event_available(event) || period == 0 || NMI_HANDLER.get().is_none() {
+            return Err(Error::InvalidArgs);
+        }
+        let period = period.min(info.max_period());
+
+        let cpu = guard.current_cpu();
+        let counters = COUNTERS.get_on_cpu(cpu);
+        let index = counters[..info.nr_counters()]
+            .iter()
+            .position(|slot| {
+                slot.handler
+                    .compare_exchange(0, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .ok_or(Error::NotEnoughResources)?;
+
+        let slot = &counters[index];
+        slot.period.store(period, Ordering::Relaxed);
+        slot.handler.store(handler as usize, Ordering::Release);
+
+        let (event_select, unit_mask) = event.select();
+        let evtsel = event_select as u64
+            | ((unit_mask as u64) << 8)
+            | PERFEVTSEL_USR
+            | PERFEVTSEL_OS
+            | PERFEVTSEL_INT
+            | PERFEVTSEL_EN;
+
+        apic::with_borrow(|apic| apic.set_lvt_pmc(LVT_PMC_NMI));
+        // SAFETY: The counter is owned by us and programming it only affects
+        // the PMU, whose interrupts are handled by `handle_nmi`.
+        unsafe {
+            wrmsr(IA32_PERFEVTSEL0 + index as u32, 0);
+            reload_counter(info, index, period);
+            wrmsr(IA32_PERFEVTSEL0 + index as u32, evtsel);
+            if info.version >= 2 {
+                let global_ctrl = rdmsr(IA32_PERF_GLOBAL_CTRL);
+                wrmsr(IA32_PERF_GLOBAL_CTRL, global_ctrl | (1 << index));
+            }
+        }
+
+        Ok(Self {
+            cpu,
+            index,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+impl Drop for SamplingCounter {
+    fn drop(&mut self) {
+        assert_eq!(
+            current_cpu_racy(),
+            self.cpu,
+            "a sampling counter must be stopped on its CPU"
+        );
+        let info = info().unwrap();
+
+        // SAFETY: The counter is owned by us and stopping it has no side effects.
+        unsafe {
+            wrmsr(IA32_PERFEVTSEL0 + self.index as u32, 0);
+            if info.version >= 2 {
+                let global_ctrl = rdmsr(IA32_PERF_GLOBAL_CTRL);
+                wrmsr(IA32_PERF_GLOBAL_CTRL, global_ctrl & !(1 << self.index));
+            }
+        }
+
+        COUNTERS.get_on_cpu(self.cpu)[self.index]
+            .handler
+            .store(0, Ordering::Release);
+    }
+}
+
+/// Sets the counter to overflow after `period` events.
+///
+/// # Safety
+///
+/// The counter must be owned by the caller.
+unsafe fn reload_counter(info: &PmuInfo, index: usize, period: u64) {
+    let value = period.wrapping_neg() & info.counter_mask();
+    // SAFETY: The counter is owned by the caller.
+    unsafe {
+        if info.has_full_width_write {
+            wrmsr(IA32_A_PMC0 + index as u32, value);
+        } else {
+            // Only the lower 32 bits are written, which are then sign-extended.
+            wrmsr(IA32_PMC0 + index as u32, value & u32::MAX as u64);
+        }
+    }
+}
+
+fn handle_nmi(trap_frame: &TrapFrame) -> NmiStatus {
+    let info = info().unwrap();
+    let counters = COUNTERS.get_on_cpu(current_cpu_racy());
+
+    let mut overflowed = 0u64;
+    for (index, slot) in counters[..info.nr_counters()].iter().enumerate() {
+        let handler = slot.handler.load(Ordering::Acquire);
+        if handler == 0 || handler == usize::MAX {
+            continue;
+        }
+
+        // A counter starts with its most significant bit set (see
+        // `reload_counter`), so the bit is cleared after it overflows.
+        // SAFETY: Reading a counter has no side effects.
+        let value = unsafe { rdmsr(IA32_PMC0 + index as u32) };
+        if value & (1 << (info.counter_width - 1)) != 0 {
+            continue;
+        }
+        overflowed |= 1 << index;
+
+        // SAFETY: The counter is in use, so it is owned by the sampling
+        // counter, which is not dropped until the NMI handler returns since
+        // it can only be dropped on this CPU.
+        unsafe { reload_counter(info, index, slot.period.load(Ordering::Relaxed)) };
+
+        // SAFETY: Valid values in `handler` are only stored by
+        // `SamplingCounter::start`, which converts them from `OverflowHandler`s.
+        let handler = unsafe { core::mem::transmute::<usize, OverflowHandler>(handler) };
+        handler(trap_frame);
+    }
+
+    if overflowed == 0 {
+        return NmiStatus::NotHandled;
+    }
+
+    if info.version >= 2 {
+        // SAFETY: Clearing the overflow status of the counters that we own has
+        // no side effects.
+        unsafe { wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, overflowed) };
+    }
+    // The local APIC masks the PMI entry after delivering a PMI, so unmask it.
+    apic::with_borrow(|apic| apic.set_lvt_pmc(LVT_PMC_NMI));
+
+    NmiStatus::Handled
+}
@@ -1,9 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! The console I/O.
+//!
+//! The console port is polled until it is handed over to a driver with [`take_console_port`].
 
-use super::device::serial::SerialPort;
-use crate::io::reserve_io_port_range;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spin::Once;
+
+use super::device::{io_port::ReadWriteAccess, serial::SerialPort};
+use crate::io::{reserve_io_port_range, IoPort};
 
 bitflags::bitflags! {
   struct LineSts: u8 {
@@ -12,7 +18,12 @@ bitflags::bitflags! {
   }
 }
 
-static CONSOLE_COM1_PORT: SerialPort = unsafe { SerialPort::new(0x3F8) };
+/// The base of the I/O ports of the console port.
+const CONSOLE_PORT_BASE: u16 = 0x3F8;
+/// The baud rate that the console port is configured with.
+const CONSOLE_BAUD_RATE: u32 = 38400;
+
+static CONSOLE_COM1_PORT: SerialPort = unsafe { SerialPort::new(CONSOLE_PORT_BASE) };
 reserve_io_port_range!(0x3F8..0x400);
 
 /// Initializes the serial port.
@@ -24,20 +35,63 @@ fn line_sts() -> LineSts {
     LineSts::from_bits_truncate(CONSOLE_COM1_PORT.line_status())
 }
 
+/// The console port that is handed over to a driver.
+pub struct ConsolePort {
+    /// The base of the I/O ports.
+    pub base: u16,
+    /// The baud rate that the port is configured with.
+    pub baud_rate: u32,
+    /// The I/O ports of the eight registers.
+    pub io_ports: [IoPort<u8, ReadWriteAccess>; 8],
+}
+
+static IS_CONSOLE_PORT_TAKEN: AtomicBool = AtomicBool::new(false);
+static CONSOLE_OUTPUT: Once<fn(u8) -> bool> = Once::new();
+
+/// Hands the console port over to a driver, which can drive the port with interrupts.
+///
+/// After this, each byte of the console output is sent with `output`, so the driver can
+/// serialize the output with its own accesses to the port. If `output` returns `false` (e.g.,
+/// the port is locked by the code that is interrupted by a kernel panic), the byte is sent by
+/// polling the port directly.
+///
+/// Returns `None` if the port has been handed over.
+pub fn take_console_port(output: fn(u8) -> bool) -> Option<ConsolePort> {
+    if IS_CONSOLE_PORT_TAKEN.swap(true, Ordering::Relaxed) {
+        return None;
+    }
+    CONSOLE_OUTPUT.call_once(|| output);
+
+    // SAFETY: The ports are the registers of the console port, which are reserved for the
+    // console. The console accesses them directly only if `output` fails.
+    let io_ports =
+        core::array::from_fn(|reg| unsafe { IoPort::new(CONSOLE_PORT_BASE + reg as u16) });
+    Some(ConsolePort {
+        base: CONSOLE_PORT_BASE,
+        baud_rate: CONSOLE_BAUD_RATE,
+        io_ports,
+    })
+}
+
 /// Sends a byte on the serial port.
 pub fn send(data: u8) {
     match data {
         8 | 0x7F => {
-            while !line_sts().contains(LineSts::OUTPUT_EMPTY) {}
-            CONSOLE_COM1_PORT.send(8);
-            while !line_sts().contains(LineSts::OUTPUT_EMPTY) {}
-            CONSOLE_COM1_PORT.send(b' ');
-            while !line_sts().contains(LineSts::OUTPUT_EMPTY) {}
-            CONSOLE_COM1_PORT.send(8);
+            send_raw(8);
+            send_raw(b' ');
+            send_raw(8);
         }
-        _ => {
-            while !line_sts().contains(LineSts::OUTPUT_EMPTY) {}
-            CONSOLE_COM1_PORT.send(data);
+        _ => send_raw(data),
+    }
+}
+
+fn send_raw(data: u8) {
+    if let Some(output) = CONSOLE_OUTPUT.get() {
+        if output(data) {
+            return;
         }
     }
+
+    while !line_sts().contains(LineSts::OUTPUT_EMPTY) {}
+    CONSOLE_COM1_PORT.send(data);
 }
@@ -11,7 +11,7 @@ use super::TIMER_FREQ;
 use crate::{
     arch::{
         kernel::apic::{self, DivideConfig},
-        timer::pit::OperatingMode,
+        timer::{hpet, pit::OperatingMode},
         tsc_freq,
     },
     trap::{IrqLine, TrapFrame},
@@ -107,6 +107,30 @@ fn init_deadline_mode_config() {
 fn init_periodic_mode_config() {
     info!("[Timer]: Enable APIC periodic mode");
 
+    // Set APIC timer count
+    apic::with_borrow(|apic| {
+        apic.set_timer_div_config(DivideConfig::Divide64);
+        apic.set_timer_init_count(0xFFFF_FFFF);
+    });
+
+    // The APIC timer counts down, so the elapsed ticks are calibrated instead.
+    let apic_freq =
+        hpet::calibrate(|| 0xFFFF_FFFF - apic::with_borrow(|apic| apic.timer_current_count()));
+    if let Some(apic_freq) = apic_freq {
+        apic::with_borrow(|apic| apic.set_timer_init_count(0));
+        let init_count = apic_freq / TIMER_FREQ;
+        info!(
+            "APIC Timer ticks count:{:x}, Timer Freq:{} Hz (calibrated with HPET)",
+            init_count, TIMER_FREQ
+        );
+        CONFIG.call_once(|| Config::PeriodicMode { init_count });
+        return;
+    }
+
+    init_periodic_mode_config_via_pit();
+}
+
+fn init_periodic_mode_config_via_pit() {
     // Allocate IRQ
     let mut irq = IrqLine::alloc().unwrap();
     irq.on_active(pit_callback);
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The broadcast timer.
+//!
+//! The local APIC timer of a CPU may stop when the CPU enters a deep C-state,
+//! unless the CPU has an always running APIC timer (ARAT). Even `hlt` can enter
+//! such a state if the firmware enables C1E promotion. On a CPU without ARAT,
+//! an idle CPU may therefore sleep through its timer ticks.
+//!
+//! To keep the ticks going, the HPET raises periodic interrupts at
+//! [`TIMER_FREQ`]. Every idle CPU that may miss its local ticks is woken up by
+//! an IPI, which delivers the tick if the local APIC timer has not done so since
+//! the last broadcast.
+
+use core::sync::atomic::Ordering;
+
+use spin::Once;
+use x86::cpuid::cpuid;
+
+use super::{hpet, TIMER_FREQ};
+use crate::{
+    cpu::{AtomicCpuSet, CpuSet, PinCurrentCpu},
+    cpu_local_cell,
+    task::disable_preempt,
+    trap::{self, IrqLine, TrapFrame},
+};
+
+/// The CPUs that are idle and may miss their local ticks.
+static BROADCAST_CPUS: Once<AtomicCpuSet> = Once::new();
+
+/// The IRQ of the HPET periodic timer.
+static HPET_IRQ: Once<IrqLine> = Once::new();
+
+/// The IRQ of the IPIs that deliver the broadcast ticks.
+static BROADCAST_IRQ: Once<IrqLine> = Once::new();
+
+cpu_local_cell! {
+    /// Whether the local APIC timer has ticked since the last broadcast.
+    static HAS_TICKED: bool = false;
+}
+
+/// Initializes the broadcast timer if the local APIC timer may stop when idle.
+pub(super) fn init() {
+    if has_arat() {
+        return;
+    }
+
+    let Some(mut hpet_irq) = hpet::start_periodic_timer(TIMER_FREQ) else {
+        log::warn!(
+            "[Timer]: The APIC timer may stop when idle, but no broadcast timer is available"
+        );
+        return;
+    };
+
+    let mut broadcast_irq = IrqLine::alloc().unwrap();
+    broadcast_irq.on_active(broadcast_callback);
+    BROADCAST_IRQ.call_once(|| broadcast_irq);
+    BROADCAST_CPUS.call_once(|| AtomicCpuSet::new(CpuSet::new_empty()));
+
+    hpet_irq.on_active(hpet_callback);
+    HPET_IRQ.call_once(|| hpet_irq);
+}
+
+/// Records that the local APIC timer has ticked on the current CPU.
+pub(super) fn on_local_tick() {
+    HAS_TICKED.store(true);
+}
+
+/// Halts the current CPU until the next interrupt, with the broadcast timer
+/// delivering the ticks that the local APIC timer may miss.
+pub(in crate::arch) fn halt() {
+    let Some(cpus) = BROADCAST_CPUS.get() else {
+        x86_64::instructions::hlt();
+        return;
+    };
+
+    // Interrupts are still handled while preemption is disabled, but the task
+    // cannot migrate before it leaves the set.
+    let preempt_guard = disable_preempt();
+    let cpu = preempt_guard.current_cpu();
+    cpus.add(cpu, Ordering::Release);
+    x86_64::instructions::hlt();
+    cpus.remove(cpu, Ordering::Relaxed);
+}
+
+/// Returns whether the local APIC timer keeps running in deep C-states.
+fn has_arat() -> bool {
+    const ARAT: u32 = 1 << 2;
+
+    // CPUID 0x06: Thermal and Power Management Leaf
+    let max_cpuid = cpuid!(0).eax;
+    max_cpuid >= 0x06 && cpuid!(0x06).eax & ARAT != 0
+}
+
+fn hpet_callback(trap_frame: &TrapFrame) {
+    let irq_guard = trap::disable_local();
+    let this_cpu = irq_guard.current_cpu();
+    let irq_num = BROADCAST_IRQ.get().unwrap().num();
+
+    let cpus = BROADCAST_CPUS.get().unwrap().load(Ordering::Acquire);
+    for cpu in cpus.iter() {
+        if cpu == this_cpu {
+            broadcast_callback(trap_frame);
+            continue;
+        }
+        // SAFETY: The IRQ is handled by `broadcast_callback`, which is safe to
+        // call on any CPU at any time.
+        unsafe { crate::arch::irq::send_ipi(cpu, irq_num) };
+    }
+}
+
+fn broadcast_callback(trap_frame: &TrapFrame) {
+    // If the local APIC timer keeps running, it has ticked in the last period.
+    if HAS_TICKED.load() {
+        HAS_TICKED.store(false);
+        return;
+    }
+
+    super::tick(trap_frame);
+}
@@ -1,7 +1,16 @@
 // SPDX-License-Identifier: MPL-2.0
 
+//! The High Precision Event Timer (HPET).
+//!
+//! The main counter of the HPET is a free-running counter with a constant
+//! frequency, which makes it a reliable clocksource to check the TSC against and
+//! a precise reference to calibrate other timers with (see [`calibrate`]).
+//!
+//! The first comparator can raise periodic interrupts, which are used as the
+//! broadcast timer (see [`super::broadcast`]).
+
 use alloc::vec::Vec;
-use core::ptr::NonNull;
+use core::{hint::spin_loop, ptr::NonNull};
 
 use acpi::{AcpiError, HpetInfo};
 use spin::Once;
@@ -11,37 +20,64 @@ use volatile::{
 };
 
 use crate::{
-    arch::kernel::{acpi::get_acpi_tables, apic::ioapic},
+    arch::kernel::{acpi::get_acpi_tables, IO_APIC},
+    io::IoMemAllocatorBuilder,
     mm::paddr_to_vaddr,
+    sync::{LocalIrqDisabled, SpinLock},
     trap::IrqLine,
 };
 
 static HPET_INSTANCE: Once<Hpet> = Once::new();
 
 const OFFSET_ID_REGISTER: usize = 0x000;
+const OFFSET_COUNTER_CLK_PERIOD_REGISTER: usize = 0x004;
 const OFFSET_CONFIGURATION_REGISTER: usize = 0x010;
 const OFFSET_INTERRUPT_STATUS_REGISTER: usize = 0x020;
-#[expect(dead_code)]
 const OFFSET_MAIN_COUNTER_VALUE_REGISTER: usize = 0x0F0;
+const OFFSET_TIMER_REGISTERS: usize = 0x100;
+const TIMER_REGISTERS_STRIDE: usize = 0x20;
+const OFFSET_TIMER_COMPARATOR_VALUE_REGISTER: usize = 0x08;
+
+/// The size of the HPET MMIO region.
+const HPET_MMIO_SIZE: usize = 0x400;
+
+/// The number of femtoseconds per second.
+///
+/// The period of the main counter is reported in femtoseconds.
+const HPET_FREQ: u64 = 1_000_000_000_000_000;
 
-#[expect(dead_code)]
-const HPET_FREQ: usize = 1_000_000_000_000_000;
+/// The bit in the configuration register that enables the main counter.
+const ENABLE_CNF: u32 = 1 << 0;
 
-#[derive(Debug)]
-#[repr(C)]
-struct HpetTimerRegister {
-    configuration_and_capabilities_register: u32,
-    timer_comparator_value_register: u32,
-    fsb_interrupt_route_register: u32,
+/// The bits in the configuration and capability register of a timer.
+const TN_INT_TYPE_CNF: u64 = 1 << 1;
+const TN_INT_ENB_CNF: u64 = 1 << 2;
+const TN_TYPE_CNF: u64 = 1 << 3;
+const TN_PER_INT_CAP: u64 = 1 << 4;
+const TN_VAL_SET_CNF: u64 = 1 << 6;
+const TN_INT_ROUTE_CNF_SHIFT: u64 = 9;
+const TN_INT_ROUTE_CNF_MASK: u64 = 0x1F << TN_INT_ROUTE_CNF_SHIFT;
+const TN_FSB_EN_CNF: u64 = 1 << 14;
+const TN_INT_ROUTE_CAP_SHIFT: u64 = 32;
+
+/// The registers of a timer (i.e., a comparator).
+struct HpetTimer {
+    configuration_and_capabilities_register: VolatileRef<'static, u64, ReadWrite>,
+    comparator_value_register: VolatileRef<'static, u64, ReadWrite>,
 }
 
-struct Hpet {
-    information_register: VolatileRef<'static, u32, ReadOnly>,
-    _general_configuration_register: VolatileRef<'static, u32, ReadWrite>,
+/// The registers that are written after the HPET is initialized.
+struct HpetControl {
+    general_configuration_register: VolatileRef<'static, u32, ReadWrite>,
     _general_interrupt_status_register: VolatileRef<'static, u32, ReadWrite>,
+    timers: Vec<HpetTimer>,
+}
 
-    _timer_registers: Vec<VolatileRef<'static, HpetTimerRegister, ReadWrite>>,
-    _irq: IrqLine,
+struct Hpet {
+    information_register: VolatileRef<'static, u32, ReadOnly>,
+    counter_clk_period_register: VolatileRef<'static, u32, ReadOnly>,
+    main_counter_value_register: VolatileRef<'static, u64, ReadOnly>,
+    control: SpinLock<HpetControl, LocalIrqDisabled>,
 }
 
 impl Hpet {
@@ -52,11 +88,18 @@ impl Hpet {
         // SAFETY: The safety is upheld by the caller.
         let (
             information_register,
-            general_configuration_register,
+            counter_clk_period_register,
+            mut general_configuration_register,
             general_interrupt_status_register,
+            main_counter_value_register,
         ) = unsafe {
             (
                 VolatileRef::new_read_only(base_address.add(OFFSET_ID_REGISTER).cast::<u32>()),
+                VolatileRef::new_read_only(
+                    base_address
+                        .add(OFFSET_COUNTER_CLK_PERIOD_REGISTER)
+                        .cast::<u32>(),
+                ),
                 VolatileRef::new(
                     base_address
                         .add(OFFSET_CONFIGURATION_REGISTER)
@@ -67,6 +110,11 @@ impl Hpet {
                         .add(OFFSET_INTERRUPT_STATUS_REGISTER)
                         .cast::<u32>(),
                 ),
+                VolatileRef::new_read_only(
+                    base_address
+                        .add(OFFSET_MAIN_COUNTER_VALUE_REGISTER)
+                        .cast::<u64>(),
+                ),
             )
         };
 
@@ -76,33 +124,45 @@ impl Hpet {
         // FIXME: We now trust the hardware. We should instead find a way to check that
         // `num_comparator` are reasonable values before proceeding.
 
-        let mut comparators = Vec::with_capacity(num_comparator);
+        let mut timers = Vec::with_capacity(num_comparator);
         for i in 0..num_comparator {
             // SAFETY: The safety is upheld by the caller and the correctness of the information
             // value.
-            let comp = unsafe {
-                VolatileRef::new(
-                    base_address
-                        .add(0x100)
-                        .add(i * 0x20)
-                        .cast::<HpetTimerRegister>(),
-                )
+            let timer = unsafe {
+                let timer_base =
+                    base_address.add(OFFSET_TIMER_REGISTERS + i * TIMER_REGISTERS_STRIDE);
+                HpetTimer {
+                    configuration_and_capabilities_register: VolatileRef::new(
+                        timer_base.cast::<u64>(),
+                    ),
+                    comparator_value_register: VolatileRef::new(
+                        timer_base
+                            .add(OFFSET_TIMER_COMPARATOR_VALUE_REGISTER)
+                            .cast::<u64>(),
+                    ),
+                }
             };
-            comparators.push(comp);
+            timers.push(timer);
         }
 
-        let mut lock = ioapic::IO_APIC.get().unwrap()[0].lock();
-        let irq = IrqLine::alloc().unwrap();
-        // FIXME: The index of HPET interrupt needs to be tested.
-        lock.enable(0, irq.clone()).unwrap();
-        drop(lock);
+        // Start the main counter if the firmware has not started it. The comparators do not
+        // generate interrupts until they are enabled.
+        let config = general_configuration_register.as_ptr().read();
+        if config & ENABLE_CNF == 0 {
+            general_configuration_register
+                .as_mut_ptr()
+                .write(config | ENABLE_CNF);
+        }
 
         Hpet {
             information_register,
-            _general_configuration_register: general_configuration_register,
-            _general_interrupt_status_register: general_interrupt_status_register,
-            _timer_registers: comparators,
-            _irq: irq,
+            counter_clk_period_register,
+            main_counter_value_register,
+            control: SpinLock::new(HpetControl {
+                general_configuration_register,
+                _general_interrupt_status_register: general_interrupt_status_register,
+                timers,
+            }),
         }
     }
 
@@ -116,7 +176,6 @@ impl Hpet {
         ((self.information_register.as_ptr().read() & 0x1F00) >> 8) as u8 + 1
     }
 
-    #[expect(dead_code)]
     pub fn main_counter_is_64bits(&self) -> bool {
         (self.information_register.as_ptr().read() & 0x2000) != 0
     }
@@ -130,20 +189,244 @@ impl Hpet {
     pub fn pci_vendor_id(&self) -> u16 {
         ((self.information_register.as_ptr().read() & 0xFFFF_0000) >> 16) as u16
     }
+
+    /// Returns the period of the main counter in femtoseconds.
+    pub fn counter_clk_period(&self) -> u32 {
+        self.counter_clk_period_register.as_ptr().read()
+    }
+
+    pub fn read_main_counter(&self) -> u64 {
+        if self.main_counter_is_64bits() {
+            self.main_counter_value_register.as_ptr().read()
+        } else {
+            self.main_counter_value_register.as_ptr().read() & u32::MAX as u64
+        }
+    }
+
+    fn counter_mask(&self) -> u64 {
+        if self.main_counter_is_64bits() {
+            u64::MAX
+        } else {
+            u32::MAX as u64
+        }
+    }
+
+    /// Returns the number of main counter ticks in `fs` femtoseconds.
+    fn fs_to_ticks(&self, fs: u64) -> u64 {
+        fs / self.counter_clk_period() as u64
+    }
+
+    /// Reads `read_counter` and the main counter at (almost) the same time.
+    ///
+    /// An SMI or a preemption by the host may delay the read of the main
+    /// counter, so the reads are retried to find the narrowest window.
+    fn read_refs(&self, read_counter: &impl Fn() -> u64) -> (u64, u64) {
+        const NR_RETRIES: usize = 5;
+
+        let mut best = (0, 0);
+        let mut best_window = u64::MAX;
+        for _ in 0..NR_RETRIES {
+            let before = read_counter();
+            let hpet_value = self.read_main_counter();
+            let window = read_counter().wrapping_sub(before);
+            if window < best_window {
+                best = (before.wrapping_add(window / 2), hpet_value);
+                best_window = window;
+            }
+        }
+        best
+    }
+}
+
+/// Returns the frequency (Hz) of the HPET main counter.
+///
+/// This method returns `None` if there is no HPET.
+pub fn counter_freq() -> Option<u64> {
+    let hpet = HPET_INSTANCE.get()?;
+    Some(HPET_FREQ / hpet.counter_clk_period() as u64)
 }
 
-/// HPET init, need to init IOAPIC before init this function
-#[expect(dead_code)]
-pub fn init() -> Result<(), AcpiError> {
-    let tables = get_acpi_tables().unwrap();
+/// Returns the mask of the valid bits of the HPET main counter.
+///
+/// # Panics
+///
+/// This method will panic if there is no HPET.
+pub fn counter_mask() -> u64 {
+    HPET_INSTANCE.get().unwrap().counter_mask()
+}
+
+/// Reads the HPET main counter.
+///
+/// # Panics
+///
+/// This method will panic if there is no HPET.
+pub fn read_counter() -> u64 {
+    HPET_INSTANCE.get().unwrap().read_main_counter()
+}
+
+/// Calibrates the frequency (Hz) of a counter against the HPET main counter.
+///
+/// `read_counter` must return a counter that increases at a constant rate. The
+/// calibration busy-waits for a few short periods and returns the median of the
+/// measured frequencies, so a period disturbed by an SMI or a preemption by the
+/// host does not skew the result.
+///
+/// This method returns `None` if there is no HPET.
+pub(in crate::arch) fn calibrate(read_counter: impl Fn() -> u64) -> Option<u64> {
+    /// The duration of each calibration period in femtoseconds (10 ms).
+    const PERIOD_FS: u64 = 10_000_000_000_000;
+    const NR_PERIODS: usize = 3;
+
+    let hpet = HPET_INSTANCE.get()?;
+    let mask = hpet.counter_mask();
+    let period_ticks = hpet.fs_to_ticks(PERIOD_FS);
+
+    let mut freqs = [0u64; NR_PERIODS];
+    for freq in freqs.iter_mut() {
+        let (counter_start, hpet_start) = hpet.read_refs(&read_counter);
+        while hpet.read_main_counter().wrapping_sub(hpet_start) & mask < period_ticks {
+            spin_loop();
+        }
+        let (counter_end, hpet_end) = hpet.read_refs(&read_counter);
+
+        let elapsed_fs =
+            (hpet_end.wrapping_sub(hpet_start) & mask) as u128 * hpet.counter_clk_period() as u128;
+        let counter_delta = counter_end.wrapping_sub(counter_start) as u128;
+        *freq = (counter_delta * HPET_FREQ as u128 / elapsed_fs) as u64;
+    }
+
+    freqs.sort_unstable();
+    Some(freqs[NR_PERIODS / 2])
+}
+
+/// Starts the first comparator of the HPET to raise interrupts periodically
+/// at the frequency of `freq` (Hz).
+///
+/// The interrupts are routed to an I/O APIC pin and handled by the returned
+/// [`IrqLine`]. This method returns `None` if there is no HPET, if the first
+/// comparator does not support the periodic mode, or if none of its pins is
+/// available.
+pub(super) fn start_periodic_timer(freq: u64) -> Option<IrqLine> {
+    let hpet = HPET_INSTANCE.get()?;
+    let mut control = hpet.control.lock();
+    let control = &mut *control;
+    let timer = control.timers.first_mut()?;
+
+    let config = timer
+        .configuration_and_capabilities_register
+        .as_ptr()
+        .read();
+    if config & TN_PER_INT_CAP == 0 {
+        return None;
+    }
+
+    // Prefer the pins that are not shared with ISA devices.
+    let route_cap = (config >> TN_INT_ROUTE_CAP_SHIFT) as u32;
+    let irq = IrqLine::alloc().ok()?;
+    let pin = {
+        let mut io_apic = IO_APIC.get()?.first()?.lock();
+        (16..32)
+            .chain(0..16)
+            .filter(|pin| route_cap & (1 << pin) != 0)
+            .find(|pin| io_apic.enable(*pin, irq.clone()).is_ok())?
+    };
+
+    let period_ticks = hpet.fs_to_ticks(HPET_FREQ / freq);
+    let config = (config & !(TN_INT_ROUTE_CNF_MASK | TN_INT_TYPE_CNF | TN_FSB_EN_CNF))
+        | ((pin as u64) << TN_INT_ROUTE_CNF_SHIFT)
+        | TN_INT_ENB_CNF
+        | TN_TYPE_CNF
+        | TN_VAL_SET_CNF;
+
+    // Stop the main counter so that the first deadline cannot be missed while
+    // the comparator is being programmed. With `TN_VAL_SET_CNF`, the first write
+    // sets the comparator and the second write sets the period.
+    let general_config = control.general_configuration_register.as_ptr().read();
+    control
+        .general_configuration_register
+        .as_mut_ptr()
+        .write(general_config & !ENABLE_CNF);
+    let now = hpet.read_main_counter();
+    timer
+        .configuration_and_capabilities_register
+        .as_mut_ptr()
+        .write(config);
+    timer
+        .comparator_value_register
+        .as_mut_ptr()
+        .write(now.wrapping_add(period_ticks));
+    timer
+        .comparator_value_register
+        .as_mut_ptr()
+        .write(period_ticks);
+    control
+        .general_configuration_register
+        .as_mut_ptr()
+        .write(general_config | ENABLE_CNF);
+
+    log::info!(
+        "[HPET]: Start the periodic timer at {} Hz on I/O APIC pin {}",
+        freq,
+        pin
+    );
+    Some(irq)
+}
+
+/// Initializes the HPET and starts its main counter.
+pub(in crate::arch) fn init(io_mem_builder: &IoMemAllocatorBuilder) -> Result<(), AcpiError> {
+    let Some(tables) = get_acpi_tables() else {
+        return Ok(());
+    };
 
     let hpet_info = HpetInfo::new(&tables)?;
     assert_ne!(hpet_info.base_address, 0, "HPET address should not be zero");
 
+    io_mem_builder.remove(hpet_info.base_address..(hpet_info.base_address + HPET_MMIO_SIZE));
+
     let base = NonNull::new(paddr_to_vaddr(hpet_info.base_address) as *mut u8).unwrap();
     // SAFETY: The base address is from the ACPI table and points to the HPET MMIO region.
     let hpet = unsafe { Hpet::new(base) };
+
+    // A period of zero or larger than 100 ns is invalid according to the HPET specification.
+    let period = hpet.counter_clk_period();
+    if period == 0 || period > 100_000_000 {
+        log::warn!("HPET reports an invalid counter period: {} fs", period);
+        return Ok(());
+    }
+
     HPET_INSTANCE.call_once(|| hpet);
 
     Ok(())
 }
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::{
+        arch::{read_tsc, tsc_freq},
+        prelude::*,
+    };
+
+    /// Returns whether `freq` is within 1% of `expected`.
+    fn is_close(freq: u64, expected: u64) -> bool {
+        freq.abs_diff(expected) <= expected / 100
+    }
+
+    #[ktest]
+    fn calibrate_hpet() {
+        let Some(hpet_freq) = counter_freq() else {
+            return;
+        };
+
+        assert!(is_close(calibrate(read_counter).unwrap(), hpet_freq));
+    }
+
+    #[ktest]
+    fn calibrate_tsc() {
+        if counter_freq().is_none() {
+            return;
+        }
+
+        assert!(is_close(calibrate(read_tsc).unwrap(), tsc_freq()));
+    }
+}
@@ -3,8 +3,10 @@
 //! The timer support.
 
 mod apic;
-mod hpet;
+pub(super) mod broadcast;
+pub mod hpet;
 pub(crate) mod pit;
+pub mod pm_timer;
 
 use core::sync::atomic::Ordering;
 
@@ -51,6 +53,10 @@ pub(super) fn init_bsp() {
 
     timer_irq.on_active(timer_callback);
     TIMER_IRQ.call_once(|| timer_irq);
+
+    if kernel::apic::exists() {
+        broadcast::init();
+    }
 }
 
 /// Enables timer interrupt on this AP.
@@ -60,7 +66,13 @@ pub(super) fn init_ap() {
     }
 }
 
-fn timer_callback(_: &TrapFrame) {
+fn timer_callback(trap_frame: &TrapFrame) {
+    broadcast::on_local_tick();
+    tick(trap_frame);
+}
+
+/// Handles a timer tick on the current CPU.
+fn tick(_: &TrapFrame) {
     let irq_guard = trap::disable_local();
     if irq_guard.current_cpu() == CpuId::bsp() {
         crate::timer::jiffies::ELAPSED.fetch_add(1, Ordering::SeqCst);
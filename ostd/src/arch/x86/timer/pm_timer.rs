@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ACPI power management timer.
+//!
+//! The PM timer is a free-running 24-bit or 32-bit counter that runs at a fixed
+//! frequency of 3.579545 MHz. It is available on almost all x86 machines with
+//! ACPI, so it is the last resort if neither the TSC nor the HPET is usable.
+//!
+//! Reference: ACPI Specification, Section 4.8.3.3 "Power Management Timer".
+
+use acpi::address::AddressSpace;
+use spin::Once;
+
+use crate::{
+    arch::{device::io_port::ReadOnlyAccess, kernel::acpi::get_platform_info},
+    io::IoPort,
+};
+
+/// The frequency (Hz) of the PM timer.
+pub const PM_TIMER_FREQ: u64 = 3_579_545;
+
+static PM_TIMER: Once<PmTimer> = Once::new();
+
+struct PmTimer {
+    port: IoPort<u32, ReadOnlyAccess>,
+    mask: u64,
+}
+
+/// Returns the mask of the valid bits of the PM timer, or `None` if there is
+/// no PM timer.
+pub fn counter_mask() -> Option<u64> {
+    PM_TIMER.get().map(|pm_timer| pm_timer.mask)
+}
+
+/// Reads the PM timer.
+///
+/// # Panics
+///
+/// This method will panic if there is no PM timer.
+pub fn read_counter() -> u64 {
+    let pm_timer = PM_TIMER.get().unwrap();
+    pm_timer.port.read() as u64 & pm_timer.mask
+}
+
+/// Initializes the PM timer.
+///
+/// This method must be called after the I/O port allocator is initialized.
+pub(in crate::arch) fn init() {
+    let Some(pm_timer) = get_platform_info().and_then(|info| info.pm_timer.as_ref()) else {
+        return;
+    };
+
+    // Hardware-reduced ACPI platforms may report the PM timer in the memory space, but the
+    // ACPI specification requires the PM timer to be in the I/O space.
+    if pm_timer.base.address_space != AddressSpace::SystemIo || pm_timer.base.address == 0 {
+        return;
+    }
+
+    let Ok(port) = IoPort::acquire(pm_timer.base.address as u16) else {
+        log::warn!("the I/O port of the PM timer is already in use");
+        return;
+    };
+    let mask = if pm_timer.supports_32bit {
+        u32::MAX as u64
+    } else {
+        (1 << 24) - 1
+    };
+
+    PM_TIMER.call_once(|| PmTimer { port, mask });
+}
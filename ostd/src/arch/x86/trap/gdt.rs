@@ -4,10 +4,11 @@
 
 use alloc::boxed::Box;
 
+use x86::cpuid::CpuId;
 use x86_64::{
-    instructions::tables::{lgdt, load_tss},
+    instructions::tables::{lgdt, load_tss, sgdt},
     registers::{
-        model_specific::Star,
+        model_specific::{Msr, Star},
         segmentation::{Segment, CS},
     },
     structures::{
@@ -18,7 +19,11 @@ use x86_64::{
     PrivilegeLevel, VirtAddr,
 };
 
-use crate::cpu::local::CpuLocal;
+use super::nmi;
+use crate::{
+    cpu::{current_cpu_racy, local::CpuLocal},
+    numa::node_of_cpu,
+};
 
 /// Initializes and loads the GDT and TSS.
 ///
@@ -33,6 +38,13 @@ use crate::cpu::local::CpuLocal;
 pub(super) unsafe fn init() {
     let tss_ptr = LOCAL_TSS.as_ptr();
 
+    // SAFETY: We're in the boot context and the TSS has not been loaded, so no one else accesses
+    // the TSS of the current CPU.
+    unsafe {
+        (*tss_ptr.cast_mut()).interrupt_stack_table[nmi::NMI_IST_INDEX as usize] =
+            VirtAddr::new(nmi::alloc_stack() as u64);
+    }
+
     // FIXME: The segment limit in the descriptor created by `tss_segment_unchecked` does not
     // include the I/O port bitmap.
 
@@ -47,9 +59,10 @@ pub(super) unsafe fn init() {
     // intended for switching to a new kernel CS.
     assert_eq!(CS::get_reg(), KERNEL_CS);
 
-    // Allocate a new GDT with 8 entries.
+    // Allocate a new GDT with 16 entries.
     let gdt = Box::new([
-        0, KCODE64, KDATA, /* UCODE32 (not used) */ 0, UDATA, UCODE64, tss0, tss1,
+        0, KCODE64, KDATA, /* UCODE32 (not used) */ 0, UDATA, UCODE64, tss0, tss1, 0, 0, 0, 0,
+        0, 0, 0, /* CPUNODE */ 0,
     ]);
     let gdt = &*Box::leak(gdt);
     assert_eq!(gdt[KERNEL_CS.index() as usize], KCODE64);
@@ -85,6 +98,57 @@ pub(super) unsafe fn init() {
     assert_eq!(gdt[(syscall.index() + 1) as usize], KDATA);
     // SAFETY: The selector points to correct kernel/user code/data descriptors in the GDT.
     unsafe { Star::write_raw(sysret.0, syscall.0) };
+
+    // SAFETY: The GDT of the current CPU has been loaded and no preemption can occur (as upheld
+    // by the caller).
+    unsafe { update_cpunode() };
+}
+
+/// Publishes the CPU ID and the NUMA node ID of the current CPU to the user space.
+///
+/// Like Linux, the IDs are encoded as `(node << 12) | cpu`, and stored in the segment limit of
+/// the CPU-node descriptor in the GDT, which can be read with the `lsl` instruction, and in the
+/// `IA32_TSC_AUX` MSR, which can be read with the `rdtscp` or `rdpid` instruction. The `getcpu`
+/// routine in the vDSO reads them without issuing system calls.
+///
+/// This should be called again if the NUMA node of the current CPU is determined after
+/// [`init`].
+///
+/// # Safety
+///
+/// The caller must ensure that [`init`] has been called on the current CPU and that no preemption
+/// can occur during the method.
+pub(in crate::arch) unsafe fn update_cpunode() {
+    let cpu = current_cpu_racy();
+    let node = node_of_cpu(cpu);
+    let cpunode = ((node.as_usize() as u64) << CPUNODE_CPU_BITS) | cpu.as_usize() as u64;
+    debug_assert!(cpu.as_usize() < (1 << CPUNODE_CPU_BITS) && cpunode < (1 << 20));
+
+    // A user-readable data descriptor whose segment limit holds the IDs.
+    let desc = (cpunode & 0xFFFF) | (((cpunode >> 16) & 0xF) << 48) | CPUNODE_DESC_ATTRS;
+
+    let gdtr = sgdt();
+    // SAFETY: The GDT of the current CPU is allocated and leaked in `init`, so it is valid for
+    // writes and contains the CPU-node descriptor. Writing the descriptor does not affect any
+    // loaded segment registers.
+    unsafe {
+        gdtr.base
+            .as_mut_ptr::<u64>()
+            .add(CPUNODE_SEL.index() as usize)
+            .write_volatile(desc)
+    };
+
+    let cpuid = CpuId::new();
+    let has_rdtscp = cpuid
+        .get_extended_processor_and_feature_identifiers()
+        .is_some_and(|info| info.has_rdtscp());
+    let has_rdpid = cpuid
+        .get_extended_feature_info()
+        .is_some_and(|info| info.has_rdpid());
+    if has_rdtscp || has_rdpid {
+        // SAFETY: Writing `IA32_TSC_AUX` only affects the values read by `rdtscp` and `rdpid`.
+        unsafe { Msr::new(IA32_TSC_AUX).write(cpunode) };
+    }
 }
 
 // The linker script makes sure that the `.cpu_local_tss` section is at the beginning of the area
@@ -122,8 +186,19 @@ pub(in crate::arch) const KCODE32: u64 = 0x00CF_9B00_0000_FFFF;
 const UCODE64: u64 = 0x00AF_FB00_0000_FFFF;
 const UDATA: u64 = 0x00CF_F300_0000_FFFF;
 
-const KERNEL_CS: SegmentSelector = SegmentSelector::new(1, PrivilegeLevel::Ring0);
-const KERNEL_SS: SegmentSelector = SegmentSelector::new(2, PrivilegeLevel::Ring0);
+pub(super) const KERNEL_CS: SegmentSelector = SegmentSelector::new(1, PrivilegeLevel::Ring0);
+pub(super) const KERNEL_SS: SegmentSelector = SegmentSelector::new(2, PrivilegeLevel::Ring0);
 
 pub(super) const USER_CS: SegmentSelector = SegmentSelector::new(5, PrivilegeLevel::Ring3);
 pub(super) const USER_SS: SegmentSelector = SegmentSelector::new(4, PrivilegeLevel::Ring3);
+
+// The CPU-node descriptor, which is hard-coded as `0x7b` in the vDSO of Linux.
+//
+// The attributes are those of a present, user-accessible (DPL 3), read-only, expand-down, accessed
+// 32-bit data segment with byte granularity. Reference:
+// <https://elixir.bootlin.com/linux/v6.2.10/source/arch/x86/kernel/cpu/common.c#L2036>
+const CPUNODE_SEL: SegmentSelector = SegmentSelector::new(15, PrivilegeLevel::Ring3);
+const CPUNODE_DESC_ATTRS: u64 = 0x0040_F500_0000_0000;
+const CPUNODE_CPU_BITS: u32 = 12;
+
+const IA32_TSC_AUX: u32 = 0xC000_0103;
@@ -12,10 +12,14 @@ use x86_64::{
     PrivilegeLevel, VirtAddr,
 };
 
+use super::nmi;
+
 global_asm!(include_str!("trap.S"));
 
 const NUM_INTERRUPTS: usize = 256;
 
+const NMI_VECTOR: usize = 2;
+
 extern "C" {
     #[link_name = "trap_handler_table"]
     static VECTORS: [usize; NUM_INTERRUPTS];
@@ -38,6 +42,9 @@ pub(super) fn init() {
 
         // Initialize the IDT entries.
         for (intr_no, &handler) in vectors.iter().enumerate() {
+            if intr_no == NMI_VECTOR {
+                continue;
+            }
             let handler = VirtAddr::new(handler as u64);
 
             let entry = &mut idt[intr_no];
@@ -51,6 +58,14 @@ pub(super) fn init() {
             }
         }
 
+        // NMIs have a dedicated entry and a dedicated stack. See `nmi.S` for details.
+        let nmi_handler = VirtAddr::new(nmi::nmi_entry as usize as u64);
+        // SAFETY: The handler defined in `nmi.S` has a correct signature to handle NMIs.
+        let opt = unsafe { idt[NMI_VECTOR].set_handler_addr(nmi_handler) };
+        // SAFETY: `gdt::init` sets up the NMI stack in the IST of every CPU before the IDT is
+        // loaded, and the stack is not used for other purposes.
+        unsafe { opt.set_stack_index(nmi::NMI_IST_INDEX) };
+
         idt
     });
 
@@ -18,6 +18,7 @@
 
 pub(super) mod gdt;
 mod idt;
+pub mod nmi;
 mod syscall;
 
 use align_ext::AlignExt;
@@ -30,6 +31,7 @@ use crate::{
     arch::{
         if_tdx_enabled,
         irq::{disable_local, enable_local},
+        kprobe,
     },
     cpu::context::{CpuException, CpuExceptionInfo, PageFaultErrorCode},
     cpu_local_cell,
@@ -106,7 +108,7 @@ pub struct TrapFrame {
 ///
 /// This function will:
 /// - Switch to a new, CPU-local [GDT].
-/// - Switch to a new, CPU-local [TSS].
+/// - Switch to a new, CPU-local [TSS], with a CPU-local stack for NMIs.
 /// - Switch to a new, global [IDT].
 /// - Enable the [`syscall`] instruction.
 ///
@@ -223,6 +225,7 @@ pub fn is_kernel_interrupted() -> bool {
 
 /// Handle traps (only from kernel).
 #[no_mangle]
+#[link_section = ".text.kprobes"]
 extern "sysv64" fn trap_handler(f: &mut TrapFrame) {
     fn enable_local_if(cond: bool) {
         if cond {
@@ -265,6 +268,8 @@ extern "sysv64" fn trap_handler(f: &mut TrapFrame) {
             }
             disable_local_if(was_irq_enabled);
         }
+        Some(CpuException::BREAKPOINT) if kprobe::handle_breakpoint(f) => {}
+        Some(CpuException::DEBUG) if kprobe::handle_debug(f) => {}
         Some(exception) => {
             enable_local_if(was_irq_enabled);
             panic!(
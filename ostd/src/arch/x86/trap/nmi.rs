@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Non-maskable interrupts (NMIs).
+//!
+//! NMIs are raised by the performance monitoring unit (see [`crate::arch::pmu`]),
+//! by other CPUs with [`send_nmi`], or by the platform (e.g., on hardware errors).
+//! They are delivered even if local IRQs are disabled, which makes them useful for
+//! sampling and for detecting CPUs that are stuck with local IRQs disabled.
+//!
+//! NMIs are handled on a dedicated per-CPU stack. Nested NMIs, which can occur
+//! if an exception is raised in an NMI handler, are coalesced with the outer
+//! NMI (see `nmi.S` for details), so NMI handlers are never reentrant.
+//!
+//! Multiple NMI sources can be latched into one NMI, so all the registered
+//! handlers are called on every NMI. A handler should check whether its source
+//! has raised the NMI and return [`NmiStatus::NotHandled`] otherwise.
+//!
+//! NMI handlers can interrupt any code, including the code that holds spin locks
+//! with local IRQs disabled. Therefore, NMI handlers must not acquire locks,
+//! allocate memory, or print logs. They should only access atomic or CPU-local
+//! data.
+
+use core::{
+    arch::global_asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::TrapFrame;
+use crate::{
+    arch::kernel::apic::{self, Icr},
+    cpu::CpuId,
+    cpu_local_cell,
+    mm::{paddr_to_vaddr, FrameAllocOptions, PAGE_SIZE},
+    Error, Result,
+};
+
+global_asm!(
+    include_str!("nmi.S"),
+    NMI_STACK_SIZE = const NMI_STACK_SIZE,
+    KERNEL_CS = const super::gdt::KERNEL_CS.0,
+    KERNEL_SS = const super::gdt::KERNEL_SS.0,
+);
+
+extern "C" {
+    pub(super) fn nmi_entry();
+}
+
+/// The index of the IST entry in the TSS for NMIs.
+pub(super) const NMI_IST_INDEX: u16 = 0;
+
+/// The size of the per-CPU NMI stack.
+const NMI_STACK_SIZE: usize = 8 * PAGE_SIZE;
+
+/// Allocates an NMI stack for the current CPU and returns its top address.
+pub(super) fn alloc_stack() -> usize {
+    let segment = FrameAllocOptions::new()
+        .zeroed(false)
+        .alloc_segment(NMI_STACK_SIZE / PAGE_SIZE)
+        .expect("failed to allocate the NMI stack");
+    // The NMI stack is used until the CPU is shut down, so it is never freed.
+    paddr_to_vaddr(segment.into_raw().end)
+}
+
+/// The status returned by an NMI handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmiStatus {
+    /// The NMI is raised by the source of the handler and has been handled.
+    Handled,
+    /// The NMI is not raised by the source of the handler.
+    NotHandled,
+}
+
+/// An NMI handler.
+///
+/// The handler is called on the CPU that receives the NMI with the trap frame
+/// of the interrupted context.
+pub type NmiHandler = fn(&TrapFrame) -> NmiStatus;
+
+/// The maximum number of NMI handlers.
+const MAX_NR_HANDLERS: usize = 8;
+
+/// The registered NMI handlers, stored as function pointers.
+///
+/// Handlers are function pointers instead of boxed closures, so unregistering
+/// a handler never frees anything that a concurrent NMI may be using.
+static HANDLERS: [AtomicUsize; MAX_NR_HANDLERS] = [const { AtomicUsize::new(0) }; MAX_NR_HANDLERS];
+
+/// The number of NMIs that no handler has handled.
+static NR_UNKNOWN_NMIS: AtomicUsize = AtomicUsize::new(0);
+
+cpu_local_cell! {
+    static IN_NMI: u8 = 0;
+}
+
+/// A registered NMI handler.
+///
+/// The handler is unregistered when the object is dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct NmiHandlerHandle {
+    slot: usize,
+}
+
+impl Drop for NmiHandlerHandle {
+    fn drop(&mut self) {
+        HANDLERS[self.slot].store(0, Ordering::Release);
+    }
+}
+
+/// Registers an NMI handler.
+///
+/// This method fails with [`Error::NotEnoughResources`] if too many handlers
+/// have been registered.
+pub fn register_handler(handler: NmiHandler) -> Result<NmiHandlerHandle> {
+    let handler = handler as usize;
+
+    let slot = HANDLERS
+        .iter()
+        .position(|slot| {
+            slot.compare_exchange(0, handler, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })
+        .ok_or(Error::NotEnoughResources)?;
+
+    Ok(NmiHandlerHandle { slot })
+}
+
+/// Returns whether the current CPU is handling an NMI.
+pub fn in_nmi() -> bool {
+    IN_NMI.load() != 0
+}
+
+/// Returns the number of NMIs that no handler has handled.
+pub fn nr_unknown_nmis() -> usize {
+    NR_UNKNOWN_NMIS.load(Ordering::Relaxed)
+}
+
+/// Sends an NMI to the specified CPU.
+///
+/// The NMI is handled by the registered handlers on the target CPU.
+pub fn send_nmi(cpu_id: CpuId) {
+    let icr = Icr::new(
+        apic::ApicId::from(cpu_id.as_usize() as u32),
+        apic::DestinationShorthand::NoShorthand,
+        apic::TriggerMode::Edge,
+        apic::Level::Assert,
+        apic::DeliveryStatus::Idle,
+        apic::DestinationMode::Physical,
+        apic::DeliveryMode::Nmi,
+        0,
+    );
+    // SAFETY: An NMI only calls the registered NMI handlers, which are safe to
+    // call at any time.
+    apic::with_borrow(|apic| unsafe { apic.send_ipi(icr) });
+}
+
+/// Handles NMIs.
+///
+/// This is called by `nmi.S` with the kernel GS base.
+#[no_mangle]
+#[link_section = ".text.kprobes"]
+extern "sysv64" fn nmi_handler(f: &mut TrapFrame) {
+    IN_NMI.add_assign(1);
+
+    let mut is_handled = false;
+    for slot in HANDLERS.iter() {
+        let handler = slot.load(Ordering::Acquire);
+        if handler == 0 {
+            continue;
+        }
+        // SAFETY: Non-zero values in `HANDLERS` are only stored by
+        // `register_handler`, which converts them from `NmiHandler`s.
+        let handler = unsafe { core::mem::transmute::<usize, NmiHandler>(handler) };
+        if handler(f) == NmiStatus::Handled {
+            is_handled = true;
+        }
+    }
+
+    if !is_handled {
+        NR_UNKNOWN_NMIS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    IN_NMI.sub_assign(1);
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::{cpu::PinCurrentCpu, prelude::*, trap::irq::disable_local};
+
+    static NR_CALLS: AtomicUsize = AtomicUsize::new(0);
+    static WAS_IN_NMI: AtomicBool = AtomicBool::new(false);
+
+    fn count_nmi(_f: &TrapFrame) -> NmiStatus {
+        NR_CALLS.fetch_add(1, Ordering::Relaxed);
+        WAS_IN_NMI.store(in_nmi(), Ordering::Relaxed);
+        NmiStatus::Handled
+    }
+
+    fn ignore_nmi(_f: &TrapFrame) -> NmiStatus {
+        NmiStatus::NotHandled
+    }
+
+    /// Sends an NMI to the current CPU and spins until `cond` holds.
+    ///
+    /// Local IRQs are disabled, since NMIs must be delivered regardless.
+    fn send_self_nmi_until(cond: impl Fn() -> bool) -> bool {
+        let irq_guard = disable_local();
+        send_nmi(irq_guard.current_cpu());
+        for _ in 0..10_000_000 {
+            if cond() {
+                return true;
+            }
+            core::hint::spin_loop();
+        }
+        false
+    }
+
+    #[ktest]
+    fn call_registered_handler() {
+        let handle = register_handler(count_nmi).unwrap();
+        let nr_unknown = nr_unknown_nmis();
+
+        assert!(send_self_nmi_until(|| NR_CALLS.load(Ordering::Relaxed) == 1));
+        assert!(WAS_IN_NMI.load(Ordering::Relaxed));
+        assert!(!in_nmi());
+        assert_eq!(nr_unknown_nmis(), nr_unknown);
+
+        // An unregistered handler is no longer called, so the next NMI is unknown.
+        drop(handle);
+        assert!(send_self_nmi_until(|| nr_unknown_nmis() > nr_unknown));
+        assert_eq!(NR_CALLS.load(Ordering::Relaxed), 1);
+    }
+
+    #[ktest]
+    fn too_many_handlers() {
+        let mut handles = Vec::new();
+        let err = loop {
+            match register_handler(ignore_nmi) {
+                Ok(handle) => handles.push(handle),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err, Error::NotEnoughResources);
+        assert!(!handles.is_empty() && handles.len() <= MAX_NR_HANDLERS);
+
+        // The slot of an unregistered handler can be reused.
+        handles.pop();
+        handles.push(register_handler(ignore_nmi).unwrap());
+    }
+}
@@ -9,7 +9,10 @@ use core::fmt::Debug;
 
 use log::{debug, error};
 
-use super::{device_info::PciDeviceId, PciCommonDevice};
+use super::{
+    device_info::{PciDeviceId, PciDeviceLocation},
+    PciCommonDevice,
+};
 use crate::bus::BusProbeError;
 
 /// PciDevice trait.
@@ -40,6 +43,7 @@ pub trait PciDriver: Sync + Send + Debug {
 /// 1. The structure that implements the PciDevice trait.
 /// 2. PCI driver.
 pub struct PciBus {
+    found_devices: Vec<(PciDeviceLocation, PciDeviceId)>,
     common_devices: VecDeque<PciCommonDevice>,
     devices: Vec<Arc<dyn PciDevice>>,
     drivers: Vec<Arc<dyn PciDriver>>,
@@ -72,9 +76,17 @@ impl PciBus {
         self.drivers.push(driver);
     }
 
+    /// Returns the locations and the IDs of all the devices found on the PCI bus,
+    /// no matter whether they have been claimed by a driver or not.
+    pub fn found_devices(&self) -> &[(PciDeviceLocation, PciDeviceId)] {
+        &self.found_devices
+    }
+
     pub(super) fn register_common_device(&mut self, mut common_device: PciCommonDevice) {
         debug!("Find pci common devices:{:x?}", common_device);
         let device_id = *common_device.device_id();
+        self.found_devices
+            .push((*common_device.location(), device_id));
         for driver in self.drivers.iter() {
             common_device = match driver.probe(common_device) {
                 Ok(device) => {
@@ -96,6 +108,7 @@ impl PciBus {
 
     pub(super) const fn new() -> Self {
         Self {
+            found_devices: Vec::new(),
             common_devices: VecDeque::new(),
             devices: Vec::new(),
             drivers: Vec::new(),
@@ -1,26 +1,51 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! MSI-X capability support.
+//!
+//! Each MSI-X vector that is bound to an IRQ line is delivered to a single CPU, which is chosen
+//! from the affinity of the IRQ line. Initially, the affinity only contains the BSP. Drivers can
+//! give a hint with [`CapabilityMsixData::set_affinity_hint`] to spread the vectors of a
+//! multi-queue device across the CPUs, and the affinity can be changed at runtime with
+//! [`set_irq_affinity`].
 
 #![expect(dead_code)]
 #![expect(unused_variables)]
 
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use spin::Once;
 
 use crate::{
     arch::{
+        self,
         iommu::has_interrupt_remapping,
-        pci::{construct_remappable_msix_address, MSIX_DEFAULT_MSG_ADDR},
+        pci::{construct_msix_address, construct_remappable_msix_address, MSIX_DEFAULT_MSG_ADDR},
     },
     bus::pci::{
         cfg_space::{Bar, Command, MemoryBar},
         common_device::PciCommonDevice,
         device_info::PciDeviceLocation,
     },
+    cpu::{CpuId, CpuSet},
     mm::VmIoOnce,
+    prelude::Result,
+    sync::SpinLock,
     trap::IrqLine,
+    Error,
 };
 
+/// The size of an entry in the MSI-X table.
+const ENTRY_SIZE: usize = 16;
+
+// The offsets of the fields in an entry of the MSI-X table.
+const ENTRY_MSG_ADDR: usize = 0;
+const ENTRY_MSG_UPPER_ADDR: usize = 4;
+const ENTRY_MSG_DATA: usize = 8;
+const ENTRY_VECTOR_CONTROL: usize = 12;
+
+/// The mask bit in the vector control field.
+const VECTOR_CONTROL_MASK: u32 = 1;
+
 /// MSI-X capability. It will set the BAR space it uses to be hidden.
 #[derive(Debug)]
 #[repr(C)]
@@ -144,41 +169,93 @@ impl CapabilityMsixData {
     }
 
     /// Enables an interrupt line, it will replace the old handle with the new handle.
+    ///
+    /// The interrupt is delivered to the BSP until the affinity is changed.
     pub fn set_interrupt_vector(&mut self, irq: IrqLine, index: u16) {
         if index >= self.table_size {
             return;
         }
 
-        // If interrupt remapping is enabled, then we need to change the value of the message address.
-        if has_interrupt_remapping() {
-            let address = construct_remappable_msix_address(&irq);
+        let mut vector = MsixVector {
+            location: self.loc,
+            index,
+            table_bar: self.table_bar.clone(),
+            entry_offset: ENTRY_SIZE * index as usize + self.table_offset,
+            irq: irq.inner_irq(),
+            affinity: CpuSet::from(CpuId::bsp()),
+            affinity_hint: None,
+            effective_cpu: CpuId::bsp(),
+        };
+        // The BSP can always be addressed.
+        vector.route_to(CpuId::bsp()).unwrap();
 
-            self.table_bar
-                .io_mem()
-                .write_once((16 * index) as usize + self.table_offset, &address)
-                .unwrap();
-            self.table_bar
-                .io_mem()
-                .write_once((16 * index + 8) as usize + self.table_offset, &0)
-                .unwrap();
-        } else {
-            self.table_bar
-                .io_mem()
-                .write_once(
-                    (16 * index + 8) as usize + self.table_offset,
-                    &(irq.num() as u32),
-                )
-                .unwrap();
+        let irq_num = irq.num();
+        let old_irq = core::mem::replace(&mut self.irqs[index as usize], Some(irq));
+        {
+            let mut vectors = VECTORS.lock();
+            if let Some(old_irq) = old_irq.as_ref() {
+                vectors.remove(&old_irq.num());
+            }
+            vectors.insert(irq_num, vector);
+        }
+        if let Some(listener) = BIND_LISTENER.get() {
+            listener(irq_num);
         }
 
-        let _old_irq = core::mem::replace(&mut self.irqs[index as usize], Some(irq));
         // Enable this msix vector
+        self.unmask(index);
+    }
+
+    /// Sets the CPUs that are suggested to handle the interrupts of a vector, and delivers the
+    /// interrupts to one of them.
+    ///
+    /// This does nothing if no interrupt line is bound to the vector. If none of the CPUs can be
+    /// addressed, only the hint is recorded.
+    pub fn set_affinity_hint(&mut self, index: u16, cpus: &CpuSet) {
+        let Some(Some(irq)) = self.irqs.get(index as usize) else {
+            return;
+        };
+
+        let mut vectors = VECTORS.lock();
+        let Some(vector) = vectors.get_mut(&irq.num()) else {
+            return;
+        };
+        vector.affinity_hint = Some(cpus.clone());
+        let _ = vector.set_affinity(cpus);
+    }
+
+    /// Masks a vector, so that its interrupts are held pending.
+    pub fn mask(&self, index: u16) {
+        if index >= self.table_size {
+            return;
+        }
+        let offset = ENTRY_SIZE * index as usize + ENTRY_VECTOR_CONTROL + self.table_offset;
         self.table_bar
             .io_mem()
-            .write_once((16 * index + 12) as usize + self.table_offset, &0_u32)
+            .write_once(offset, &VECTOR_CONTROL_MASK)
             .unwrap();
     }
 
+    /// Unmasks a vector, so that the pending interrupt (if any) is sent.
+    pub fn unmask(&self, index: u16) {
+        if index >= self.table_size {
+            return;
+        }
+        let offset = ENTRY_SIZE * index as usize + ENTRY_VECTOR_CONTROL + self.table_offset;
+        self.table_bar.io_mem().write_once(offset, &0_u32).unwrap();
+    }
+
+    /// Returns true if a vector has a pending interrupt.
+    pub fn is_pending(&self, index: u16) -> bool {
+        if index >= self.table_size {
+            return false;
+        }
+        // The pending bits are packed in 64-bit words.
+        let offset = (index as usize / 64) * 8 + self.pending_table_offset;
+        let bits: u64 = self.pending_table_bar.io_mem().read_once(offset).unwrap();
+        bits & (1 << (index % 64)) != 0
+    }
+
     /// Gets mutable IrqLine. User can register callbacks by using this function.
     pub fn irq_mut(&mut self, index: usize) -> Option<&mut IrqLine> {
         self.irqs[index].as_mut()
@@ -194,3 +271,132 @@ impl CapabilityMsixData {
 fn set_bit(origin_value: u16, offset: usize, set: bool) -> u16 {
     (origin_value & (!(1 << offset))) | ((set as u16) << offset)
 }
+
+/// The affinity of an IRQ line that is bound to an MSI-X vector.
+#[derive(Debug, Clone)]
+pub struct IrqAffinity {
+    /// The location of the PCI device.
+    pub location: PciDeviceLocation,
+    /// The index of the vector in the MSI-X table.
+    pub index: u16,
+    /// The CPUs that are allowed to handle the interrupts.
+    pub affinity: CpuSet,
+    /// The CPU that the interrupts are delivered to.
+    pub effective_cpu: CpuId,
+    /// The CPUs that are suggested by the driver, if any.
+    pub affinity_hint: Option<CpuSet>,
+}
+
+/// Returns the affinity of an IRQ line, or `None` if the IRQ line is not bound to an MSI-X
+/// vector.
+pub fn irq_affinity(irq_num: u8) -> Option<IrqAffinity> {
+    let vectors = VECTORS.lock();
+    let vector = vectors.get(&irq_num)?;
+    Some(IrqAffinity {
+        location: vector.location,
+        index: vector.index,
+        affinity: vector.affinity.clone(),
+        effective_cpu: vector.effective_cpu,
+        affinity_hint: vector.affinity_hint.clone(),
+    })
+}
+
+/// Sets the CPUs that are allowed to handle the interrupts of an IRQ line that is bound to an
+/// MSI-X vector.
+///
+/// The interrupts are delivered to one of the CPUs. This method fails if the IRQ line is not
+/// bound to an MSI-X vector, or if none of the CPUs can be addressed.
+pub fn set_irq_affinity(irq_num: u8, cpus: &CpuSet) -> Result<()> {
+    let mut vectors = VECTORS.lock();
+    let vector = vectors.get_mut(&irq_num).ok_or(Error::InvalidArgs)?;
+    vector.set_affinity(cpus)
+}
+
+/// Returns the numbers of the IRQ lines that are bound to MSI-X vectors.
+pub fn bound_irqs() -> Vec<u8> {
+    VECTORS.lock().keys().copied().collect()
+}
+
+/// Registers a function that is called with the IRQ number after an IRQ line is bound to an
+/// MSI-X vector.
+///
+/// This function can only be registered once. Subsequent calls will do nothing.
+pub fn register_bind_listener(func: fn(u8)) {
+    BIND_LISTENER.call_once(|| func);
+}
+
+/// The MSI-X vectors that are bound to IRQ lines, indexed by the IRQ numbers.
+static VECTORS: SpinLock<BTreeMap<u8, MsixVector>> = SpinLock::new(BTreeMap::new());
+
+static BIND_LISTENER: Once<fn(u8)> = Once::new();
+
+/// An MSI-X vector that is bound to an IRQ line.
+#[derive(Debug)]
+struct MsixVector {
+    location: PciDeviceLocation,
+    index: u16,
+    table_bar: Arc<MemoryBar>,
+    /// The offset of the table entry in the BAR.
+    entry_offset: usize,
+    irq: &'static arch::irq::IrqLine,
+    affinity: CpuSet,
+    affinity_hint: Option<CpuSet>,
+    effective_cpu: CpuId,
+}
+
+impl MsixVector {
+    fn set_affinity(&mut self, cpus: &CpuSet) -> Result<()> {
+        // Keep the current CPU if possible, so that the vector is not reprogrammed needlessly.
+        if cpus.contains(self.effective_cpu) {
+            self.affinity = cpus.clone();
+            return Ok(());
+        }
+
+        let cpu = cpus
+            .iter()
+            .find(|cpu| has_interrupt_remapping() || construct_msix_address(*cpu).is_some())
+            .ok_or(Error::InvalidArgs)?;
+        self.route_to(cpu)?;
+        self.affinity = cpus.clone();
+        Ok(())
+    }
+
+    /// Programs the table entry to deliver the interrupts to the CPU.
+    fn route_to(&mut self, cpu: CpuId) -> Result<()> {
+        // If interrupt remapping is enabled, then we need to change the value of the message address.
+        let (address, data) = if has_interrupt_remapping() {
+            (construct_remappable_msix_address(self.irq, cpu), 0)
+        } else {
+            let address = construct_msix_address(cpu).ok_or(Error::InvalidArgs)?;
+            (address, self.irq.num() as u32)
+        };
+
+        // Mask the vector while the entry is being updated, so that no interrupt is sent with a
+        // partially updated message.
+        let io_mem = self.table_bar.io_mem();
+        let control: u32 = io_mem
+            .read_once(self.entry_offset + ENTRY_VECTOR_CONTROL)
+            .unwrap();
+        io_mem
+            .write_once(
+                self.entry_offset + ENTRY_VECTOR_CONTROL,
+                &(control | VECTOR_CONTROL_MASK),
+            )
+            .unwrap();
+        io_mem
+            .write_once(self.entry_offset + ENTRY_MSG_ADDR, &address)
+            .unwrap();
+        io_mem
+            .write_once(self.entry_offset + ENTRY_MSG_UPPER_ADDR, &0_u32)
+            .unwrap();
+        io_mem
+            .write_once(self.entry_offset + ENTRY_MSG_DATA, &data)
+            .unwrap();
+        io_mem
+            .write_once(self.entry_offset + ENTRY_VECTOR_CONTROL, &control)
+            .unwrap();
+
+        self.effective_cpu = cpu;
+        Ok(())
+    }
+}
@@ -6,11 +6,19 @@
 
 use alloc::vec::Vec;
 
+use log::warn;
+
 use super::{
     capability::Capability,
     cfg_space::{AddrLen, Bar, Command, PciDeviceCommonCfgOffset, Status},
     device_info::{PciDeviceId, PciDeviceLocation},
 };
+use crate::arch::iommu::IommuError;
+
+/// The start offset of the device-specific region in the configuration space.
+const DEVICE_SPECIFIC_CONFIG_START: u16 = 0x40;
+/// The end offset of the device-specific region in the (legacy) configuration space.
+const DEVICE_SPECIFIC_CONFIG_END: u16 = 0x100;
 
 /// PCI common device, Contains a range of information and functions common to PCI devices.
 #[derive(Debug)]
@@ -51,7 +59,13 @@ impl PciCommonDevice {
     }
 
     /// Sets the PCI Command
+    ///
+    /// If the bus mastering is enabled, the device is attached to the IOMMU first, so that it can
+    /// access the memory that is mapped for it.
     pub fn set_command(&self, command: Command) {
+        if command.contains(Command::BUS_MASTER) {
+            self.attach_to_iommu();
+        }
         self.location
             .write16(PciDeviceCommonCfgOffset::Command as u16, command.bits())
     }
@@ -64,6 +78,51 @@ impl PciCommonDevice {
         )
     }
 
+    /// Reads a byte from the device-specific region of the configuration space.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the offset is not in the device-specific region, which starts
+    /// after the common configuration header.
+    pub fn read_device_config8(&self, offset: u16) -> u8 {
+        assert!((DEVICE_SPECIFIC_CONFIG_START..DEVICE_SPECIFIC_CONFIG_END).contains(&offset));
+        self.location.read8(offset)
+    }
+
+    /// Reads a word from the device-specific region of the configuration space.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the offset is not in the device-specific region or is not
+    /// aligned to two bytes.
+    pub fn read_device_config16(&self, offset: u16) -> u16 {
+        assert!((DEVICE_SPECIFIC_CONFIG_START..DEVICE_SPECIFIC_CONFIG_END).contains(&offset));
+        assert!(offset % 2 == 0);
+        self.location.read16(offset)
+    }
+
+    /// Writes a byte to the device-specific region of the configuration space.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the offset is not in the device-specific region.
+    pub fn write_device_config8(&self, offset: u16, value: u8) {
+        assert!((DEVICE_SPECIFIC_CONFIG_START..DEVICE_SPECIFIC_CONFIG_END).contains(&offset));
+        self.location.write8(offset, value)
+    }
+
+    /// Writes a word to the device-specific region of the configuration space.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the offset is not in the device-specific region or is not
+    /// aligned to two bytes.
+    pub fn write_device_config16(&self, offset: u16, value: u16) {
+        assert!((DEVICE_SPECIFIC_CONFIG_START..DEVICE_SPECIFIC_CONFIG_END).contains(&offset));
+        assert!(offset % 2 == 0);
+        self.location.write16(offset, value)
+    }
+
     pub(super) fn new(location: PciDeviceLocation) -> Option<Self> {
         if location.read16(0) == 0xFFFF {
             // not exists
@@ -79,10 +138,26 @@ impl PciCommonDevice {
             bar_manager,
             capabilities,
         };
+        // The firmware may have enabled the bus mastering.
+        if device.command().contains(Command::BUS_MASTER) {
+            device.attach_to_iommu();
+        }
         device.capabilities = Capability::device_capabilities(&mut device);
         Some(device)
     }
 
+    fn attach_to_iommu(&self) {
+        let Err(err) = crate::arch::iommu::attach_device(self.location) else {
+            return;
+        };
+        if !matches!(err, IommuError::NoIommu) {
+            warn!(
+                "failed to attach PCI device {:x?} to the IOMMU: {:?}",
+                self.location, err
+            );
+        }
+    }
+
     pub(super) fn bar_manager_mut(&mut self) -> &mut BarManager {
         &mut self.bar_manager
     }
@@ -90,22 +90,12 @@ impl PciDeviceLocation {
             },
         )
     }
-
-    /// The page table of all devices is the same. So we can use any device ID.
-    /// FIXME: distinguish different device id.
-    pub fn zero() -> Self {
-        Self {
-            bus: 0,
-            device: 0,
-            function: 0,
-        }
-    }
 }
 
 impl PciDeviceLocation {
     pub(super) const BIT32_ALIGN_MASK: u16 = 0xFFFC;
 
-    pub(super) fn read8(&self, offset: u16) -> u8 {
+    pub(crate) fn read8(&self, offset: u16) -> u8 {
         let val = self.read32(offset & Self::BIT32_ALIGN_MASK);
         ((val >> ((offset as usize & 0b11) << 3)) & 0xFF) as u8
     }
@@ -4,8 +4,10 @@
 
 pub mod local;
 pub mod set;
+pub(crate) mod topology;
 
 pub use set::{AtomicCpuSet, CpuSet};
+pub use topology::smt_siblings;
 
 pub use crate::arch::cpu::*;
 use crate::{cpu_local_cell, task::atomic_mode::InAtomicMode};
@@ -167,6 +169,8 @@ pub(crate) unsafe fn init_on_bsp() {
         // See its implementation for details.
         init_num_cpus(num_cpus);
     }
+
+    topology::init_current(CpuId::bsp());
 }
 
 /// # Safety
@@ -177,4 +181,6 @@ pub(crate) unsafe fn init_on_bsp() {
 pub(crate) unsafe fn init_on_ap(cpu_id: u32) {
     // SAFETY: The safety is upheld by the caller.
     unsafe { set_this_cpu_id(cpu_id) };
+
+    topology::init_current(CpuId(cpu_id));
 }
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! CPU topology.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::{all_cpus, CpuId, CpuSet};
+use crate::cpu_local;
+
+/// The core ID of a CPU whose topology is unknown.
+///
+/// Such a CPU is considered to have no SMT siblings.
+const UNKNOWN_CORE_ID: u32 = u32::MAX;
+
+/// The hardware ID of a CPU that is unknown.
+const UNKNOWN_HW_ID: u32 = u32::MAX;
+
+cpu_local! {
+    static CORE_ID: AtomicU32 = AtomicU32::new(UNKNOWN_CORE_ID);
+    static HW_ID: AtomicU32 = AtomicU32::new(UNKNOWN_HW_ID);
+}
+
+/// Records the topology of the current CPU.
+pub(super) fn init_current(cpu_id: CpuId) {
+    if let Some(core_id) = crate::arch::cpu::current_core_id() {
+        CORE_ID.get_on_cpu(cpu_id).store(core_id, Ordering::Relaxed);
+    }
+    if let Some(hw_id) = crate::arch::cpu::current_hw_id() {
+        HW_ID.get_on_cpu(cpu_id).store(hw_id, Ordering::Relaxed);
+    }
+}
+
+/// Returns the hardware ID of a CPU (e.g., the APIC ID on x86), if known.
+pub(crate) fn hw_id(cpu_id: CpuId) -> Option<u32> {
+    let hw_id = HW_ID.get_on_cpu(cpu_id).load(Ordering::Relaxed);
+    (hw_id != UNKNOWN_HW_ID).then_some(hw_id)
+}
+
+/// Returns the SMT siblings of a CPU.
+///
+/// The SMT siblings of a CPU are the logical CPUs on the same physical core,
+/// which share the execution resources, including the CPU itself.
+pub fn smt_siblings(cpu_id: CpuId) -> CpuSet {
+    let mut siblings = CpuSet::new_empty();
+    siblings.add(cpu_id);
+
+    let core_id = CORE_ID.get_on_cpu(cpu_id).load(Ordering::Relaxed);
+    if core_id == UNKNOWN_CORE_ID {
+        return siblings;
+    }
+
+    for cpu in all_cpus() {
+        if CORE_ID.get_on_cpu(cpu).load(Ordering::Relaxed) == core_id {
+            siblings.add(cpu);
+        }
+    }
+    siblings
+}
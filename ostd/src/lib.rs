@@ -39,6 +39,7 @@ mod error;
 pub mod io;
 pub mod logger;
 pub mod mm;
+pub mod numa;
 pub mod panic;
 pub mod prelude;
 pub mod smp;
@@ -8,6 +8,7 @@ use cfg_if::cfg_if;
 use super::{check_and_insert_dma_mapping, remove_dma_mapping, DmaError, HasDaddr};
 use crate::{
     arch::iommu,
+    bus::pci::PciDeviceLocation,
     mm::{
         dma::{dma_type, Daddr, DmaType},
         io::VmIoOnce,
@@ -41,6 +42,8 @@ struct DmaCoherentInner {
     segment: USegment,
     start_daddr: Daddr,
     is_cache_coherent: bool,
+    /// The device that the mapping is made for, or `None` if it is made for all devices.
+    device: Option<PciDeviceLocation>,
 }
 
 impl DmaCoherent {
@@ -54,6 +57,26 @@ impl DmaCoherent {
     /// The method fails if any part of the given `segment`
     /// already belongs to a DMA mapping.
     pub fn map(segment: USegment, is_cache_coherent: bool) -> core::result::Result<Self, DmaError> {
+        Self::map_inner(segment, is_cache_coherent, None)
+    }
+
+    /// Creates a coherent DMA mapping backed by `segment` for a PCI device.
+    ///
+    /// Unlike [`Self::map`], the mapping can only be accessed by the device
+    /// if DMA remapping is enabled.
+    pub fn map_for_device(
+        segment: USegment,
+        is_cache_coherent: bool,
+        device: PciDeviceLocation,
+    ) -> core::result::Result<Self, DmaError> {
+        Self::map_inner(segment, is_cache_coherent, Some(device))
+    }
+
+    fn map_inner(
+        segment: USegment,
+        is_cache_coherent: bool,
+        device: Option<PciDeviceLocation>,
+    ) -> core::result::Result<Self, DmaError> {
         let frame_count = segment.size() / PAGE_SIZE;
         let start_paddr = segment.start_paddr();
         if !check_and_insert_dma_mapping(start_paddr, frame_count) {
@@ -88,12 +111,9 @@ impl DmaCoherent {
                 start_paddr as Daddr
             }
             DmaType::Iommu => {
-                for i in 0..frame_count {
-                    let paddr = start_paddr + (i * PAGE_SIZE);
-                    // SAFETY: the `paddr` is restricted by the `start_paddr` and `frame_count` of the `segment`.
-                    unsafe {
-                        iommu::map(paddr as Daddr, paddr).unwrap();
-                    }
+                // SAFETY: the pages are restricted by the `start_paddr` and `frame_count` of the `segment`.
+                unsafe {
+                    iommu::map(device, start_paddr as Daddr, start_paddr, frame_count).unwrap();
                 }
                 start_paddr as Daddr
             }
@@ -103,6 +123,7 @@ impl DmaCoherent {
                 segment,
                 start_daddr,
                 is_cache_coherent,
+                device,
             }),
         })
     }
@@ -147,10 +168,7 @@ impl Drop for DmaCoherentInner {
                 });
             }
             DmaType::Iommu => {
-                for i in 0..frame_count {
-                    let paddr = start_paddr + (i * PAGE_SIZE);
-                    iommu::unmap(paddr).unwrap();
-                }
+                iommu::unmap(self.device, start_paddr as Daddr, frame_count).unwrap();
             }
         }
         if !self.is_cache_coherent {
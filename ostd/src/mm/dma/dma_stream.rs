@@ -6,6 +6,7 @@ use core::ops::Range;
 use super::{check_and_insert_dma_mapping, remove_dma_mapping, DmaError, HasDaddr};
 use crate::{
     arch::iommu,
+    bus::pci::PciDeviceLocation,
     error::Error,
     mm::{
         dma::{dma_type, Daddr, DmaType},
@@ -31,6 +32,8 @@ struct DmaStreamInner {
     #[expect(unused)]
     is_cache_coherent: bool,
     direction: DmaDirection,
+    /// The device that the mapping is made for, or `None` if it is made for all devices.
+    device: Option<PciDeviceLocation>,
 }
 
 /// `DmaDirection` limits the data flow direction of [`DmaStream`] and
@@ -53,6 +56,28 @@ impl DmaStream {
         segment: USegment,
         direction: DmaDirection,
         is_cache_coherent: bool,
+    ) -> Result<Self, DmaError> {
+        Self::map_inner(segment, direction, is_cache_coherent, None)
+    }
+
+    /// Establishes DMA stream mapping for a given [`USegment`] for a PCI device.
+    ///
+    /// Unlike [`Self::map`], the mapping can only be accessed by the device
+    /// if DMA remapping is enabled.
+    pub fn map_for_device(
+        segment: USegment,
+        direction: DmaDirection,
+        is_cache_coherent: bool,
+        device: PciDeviceLocation,
+    ) -> Result<Self, DmaError> {
+        Self::map_inner(segment, direction, is_cache_coherent, Some(device))
+    }
+
+    fn map_inner(
+        segment: USegment,
+        direction: DmaDirection,
+        is_cache_coherent: bool,
+        device: Option<PciDeviceLocation>,
     ) -> Result<Self, DmaError> {
         let frame_count = segment.size() / PAGE_SIZE;
         let start_paddr = segment.start_paddr();
@@ -78,12 +103,9 @@ impl DmaStream {
                 start_paddr as Daddr
             }
             DmaType::Iommu => {
-                for i in 0..frame_count {
-                    let paddr = start_paddr + (i * PAGE_SIZE);
-                    // SAFETY: the `paddr` is restricted by the `start_paddr` and `frame_count` of the `segment`.
-                    unsafe {
-                        iommu::map(paddr as Daddr, paddr).unwrap();
-                    }
+                // SAFETY: the pages are restricted by the `start_paddr` and `frame_count` of the `segment`.
+                unsafe {
+                    iommu::map(device, start_paddr as Daddr, start_paddr, frame_count).unwrap();
                 }
                 start_paddr as Daddr
             }
@@ -95,6 +117,7 @@ impl DmaStream {
                 start_daddr,
                 is_cache_coherent,
                 direction,
+                device,
             }),
         })
     }
@@ -188,10 +211,7 @@ impl Drop for DmaStreamInner {
                 });
             }
             DmaType::Iommu => {
-                for i in 0..frame_count {
-                    let paddr = start_paddr + (i * PAGE_SIZE);
-                    iommu::unmap(paddr).unwrap();
-                }
+                iommu::unmap(self.device, start_paddr as Daddr, frame_count).unwrap();
             }
         }
         remove_dma_mapping(start_paddr, frame_count);
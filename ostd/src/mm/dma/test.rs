@@ -3,6 +3,7 @@
 use alloc::vec;
 
 use crate::{
+    bus::pci::PciDeviceLocation,
     mm::{
         dma::*,
         io::{VmIo, VmIoOnce},
@@ -49,6 +50,26 @@ mod dma_coherent {
         assert_eq!(err, DmaError::AlreadyMapped);
     }
 
+    #[ktest]
+    fn map_for_device() {
+        let segment = FrameAllocOptions::new()
+            .alloc_segment_with(1, |_| ())
+            .unwrap();
+        let device = PciDeviceLocation {
+            bus: 0,
+            device: 31,
+            function: 7,
+        };
+        let dma_coherent =
+            DmaCoherent::map_for_device(segment.clone().into(), true, device).unwrap();
+        assert_eq!(dma_coherent.daddr(), segment.start_paddr());
+        let err = DmaCoherent::map(segment.clone().into(), true).unwrap_err();
+        assert_eq!(err, DmaError::AlreadyMapped);
+
+        drop(dma_coherent);
+        let _dma_coherent = DmaCoherent::map(segment.into(), true).unwrap();
+    }
+
     #[ktest]
     fn read_write() {
         let segment = FrameAllocOptions::new()
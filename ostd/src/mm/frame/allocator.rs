@@ -12,6 +12,7 @@ use crate::{
     error::Error,
     impl_frame_meta_for,
     mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
+    numa::{node_of_paddr, NodeId},
     prelude::*,
     util::ops::range_difference,
 };
@@ -19,6 +20,7 @@ use crate::{
 /// Options for allocating physical memory frames.
 pub struct FrameAllocOptions {
     zeroed: bool,
+    node_id: Option<NodeId>,
 }
 
 impl Default for FrameAllocOptions {
@@ -30,7 +32,10 @@ impl Default for FrameAllocOptions {
 impl FrameAllocOptions {
     /// Creates new options for allocating the specified number of frames.
     pub fn new() -> Self {
-        Self { zeroed: true }
+        Self {
+            zeroed: true,
+            node_id: None,
+        }
     }
 
     /// Sets whether the allocated frames should be initialized with zeros.
@@ -45,6 +50,23 @@ impl FrameAllocOptions {
         self
     }
 
+    /// Sets the NUMA node where the frames should be allocated.
+    ///
+    /// If the node is set, the allocation fails if there is not enough free
+    /// memory on the node. By default, the frames can be allocated on any node.
+    pub fn node(&mut self, node_id: NodeId) -> &mut Self {
+        self.node_id = Some(node_id);
+        self
+    }
+
+    fn alloc_paddr(&self, layout: Layout) -> Option<Paddr> {
+        let allocator = get_global_frame_allocator();
+        match self.node_id {
+            Some(node_id) => allocator.alloc_on_node(layout, node_id),
+            None => allocator.alloc(layout),
+        }
+    }
+
     /// Allocates a single untyped frame without metadata.
     pub fn alloc_frame(&self) -> Result<Frame<()>> {
         self.alloc_frame_with(())
@@ -53,8 +75,8 @@ impl FrameAllocOptions {
     /// Allocates a single frame with additional metadata.
     pub fn alloc_frame_with<M: AnyFrameMeta>(&self, metadata: M) -> Result<Frame<M>> {
         let single_layout = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
-        let frame = get_global_frame_allocator()
-            .alloc(single_layout)
+        let frame = self
+            .alloc_paddr(single_layout)
             .map(|paddr| Frame::from_unused(paddr, metadata).unwrap())
             .ok_or(Error::NoMemory)?;
 
@@ -88,8 +110,8 @@ impl FrameAllocOptions {
             return Err(Error::InvalidArgs);
         }
         let layout = Layout::from_size_align(nframes * PAGE_SIZE, PAGE_SIZE).unwrap();
-        let segment = get_global_frame_allocator()
-            .alloc(layout)
+        let segment = self
+            .alloc_paddr(layout)
             .map(|start| {
                 Segment::from_unused(start..start + nframes * PAGE_SIZE, metadata_fn).unwrap()
             })
@@ -153,6 +175,24 @@ pub trait GlobalFrameAllocator: Sync {
     /// allocated, they may be returned in any order with any number of calls.
     fn alloc(&self, layout: Layout) -> Option<Paddr>;
 
+    /// Allocates a contiguous range of frames on the NUMA node.
+    ///
+    /// The caller guarantees the same as [`GlobalFrameAllocator::alloc`]. The
+    /// allocated frames are deallocated in the same way as well.
+    ///
+    /// The default implementation does not look for the free memory on the
+    /// node. It only succeeds if [`GlobalFrameAllocator::alloc`] happens to
+    /// return the memory on the node. Allocators that are aware of the NUMA
+    /// topology should override it.
+    fn alloc_on_node(&self, layout: Layout, node_id: NodeId) -> Option<Paddr> {
+        let addr = self.alloc(layout)?;
+        if node_of_paddr(addr) == node_id && node_of_paddr(addr + layout.size() - 1) == node_id {
+            return Some(addr);
+        }
+        self.dealloc(addr, layout.size());
+        None
+    }
+
     /// Deallocates a contiguous range of frames.
     ///
     /// The caller guarantees that `addr` and `size` are both aligned to
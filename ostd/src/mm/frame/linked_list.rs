@@ -276,6 +276,16 @@ where
         })
     }
 
+    /// Gets the physical address of the current frame.
+    ///
+    /// Returns `None` if the cursor is pointing to the "ghost" non-element.
+    pub fn current_paddr(&self) -> Option<Paddr> {
+        self.current.map(|current| {
+            let meta_ptr = current.as_ptr() as *mut MetaSlot;
+            mapping::meta_to_frame::<PagingConsts>(meta_ptr as Vaddr)
+        })
+    }
+
     /// Takes the current pointing frame out of the linked list.
     ///
     /// If successful, the frame is returned and the cursor is moved to the
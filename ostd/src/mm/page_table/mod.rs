@@ -286,16 +286,6 @@ impl<'a, M: PageTableMode, E: PageTableEntryTrait, C: PagingConstsTrait> PageTab
     pub fn cursor(&'a self, va: &Range<Vaddr>) -> Result<Cursor<'a, M, E, C>, PageTableError> {
         Cursor::new(self, va)
     }
-
-    /// Create a new reference to the same page table.
-    /// The caller must ensure that the kernel page table is not copied.
-    /// This is only useful for IOMMU page tables. Think twice before using it in other cases.
-    pub unsafe fn shallow_copy(&self) -> Self {
-        PageTable {
-            root: self.root.clone_shallow(),
-            _phantom: PhantomData,
-        }
-    }
 }
 
 /// A software emulation of the MMU address translation process.
@@ -91,6 +91,14 @@ impl VmSpace {
         }
     }
 
+    /// Returns the CPUs on which this VM space is activated.
+    ///
+    /// The result may be stale, since the VM space can be activated or deactivated on other
+    /// CPUs concurrently.
+    pub fn activated_cpus(&self) -> CpuSet {
+        self.cpus.load(Ordering::Acquire)
+    }
+
     /// Returns the ASID of this VM space.
     pub fn asid(&self) -> u16 {
         self.asid
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Non-uniform memory access (NUMA) topology.
+//!
+//! On a NUMA machine, the CPUs and the physical memory are grouped into
+//! _nodes_. Accessing the memory on the local node is faster than accessing
+//! the memory on a remote node.
+//!
+//! If the platform does not describe the NUMA topology, the whole machine is
+//! considered to be a single node.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use spin::Once;
+
+use crate::{
+    cpu::{all_cpus, topology, CpuId, CpuSet},
+    mm::{Paddr, PAGE_SIZE},
+};
+
+/// The ID of a NUMA node.
+///
+/// The IDs are numbered from zero without holes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Returns the first node, which always exists.
+    pub const fn first() -> Self {
+        NodeId(0)
+    }
+
+    /// Converts the node ID to `usize`.
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<usize> for NodeId {
+    type Error = &'static str;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value < num_nodes() {
+            Ok(NodeId(value as u32))
+        } else {
+            Err("The given node ID is out of range")
+        }
+    }
+}
+
+struct NumaInfo {
+    nr_nodes: usize,
+    /// The nodes of the CPUs, indexed by their hardware IDs.
+    cpu_nodes: BTreeMap<u32, NodeId>,
+    /// The nodes of the memory ranges.
+    memory_nodes: Vec<(Range<Paddr>, NodeId)>,
+}
+
+static NUMA_INFO: Once<NumaInfo> = Once::new();
+
+/// Initializes the NUMA topology.
+///
+/// The affinities associate the CPUs (by their hardware IDs) and the memory
+/// ranges with the platform-specific _proximity domains_, which are mapped to
+/// the node IDs in ascending order.
+pub(crate) fn init(
+    cpu_affinities: impl Iterator<Item = (u32, u32)>,
+    memory_affinities: impl Iterator<Item = (Range<Paddr>, u32)>,
+) {
+    let cpu_affinities: Vec<(u32, u32)> = cpu_affinities.collect();
+    let memory_affinities: Vec<(Range<Paddr>, u32)> = memory_affinities.collect();
+
+    let mut domains: BTreeMap<u32, NodeId> = BTreeMap::new();
+    for &(_, domain) in cpu_affinities.iter() {
+        domains.insert(domain, NodeId::first());
+    }
+    for (_, domain) in memory_affinities.iter() {
+        domains.insert(*domain, NodeId::first());
+    }
+    if domains.len() <= 1 {
+        return;
+    }
+    for (i, node) in domains.values_mut().enumerate() {
+        *node = NodeId(i as u32);
+    }
+
+    let cpu_nodes = cpu_affinities
+        .into_iter()
+        .map(|(hw_id, domain)| (hw_id, domains[&domain]))
+        .collect();
+    let memory_nodes = memory_affinities
+        .into_iter()
+        .map(|(range, domain)| (range, domains[&domain]))
+        .collect();
+
+    log::info!("NUMA: found {} nodes", domains.len());
+    NUMA_INFO.call_once(|| NumaInfo {
+        nr_nodes: domains.len(),
+        cpu_nodes,
+        memory_nodes,
+    });
+}
+
+/// Returns the number of NUMA nodes.
+pub fn num_nodes() -> usize {
+    NUMA_INFO.get().map_or(1, |info| info.nr_nodes)
+}
+
+/// Returns the node that a CPU belongs to.
+pub fn node_of_cpu(cpu_id: CpuId) -> NodeId {
+    let Some(info) = NUMA_INFO.get() else {
+        return NodeId::first();
+    };
+    topology::hw_id(cpu_id)
+        .and_then(|hw_id| info.cpu_nodes.get(&hw_id).copied())
+        .unwrap_or(NodeId::first())
+}
+
+/// Returns the CPUs that belong to a node.
+pub fn cpus_of_node(node_id: NodeId) -> CpuSet {
+    let mut cpus = CpuSet::new_empty();
+    for cpu in all_cpus() {
+        if node_of_cpu(cpu) == node_id {
+            cpus.add(cpu);
+        }
+    }
+    cpus
+}
+
+/// Returns the node that a physical address belongs to.
+///
+/// The addresses not described by the platform are considered to be on the
+/// first node.
+pub fn node_of_paddr(paddr: Paddr) -> NodeId {
+    let Some(info) = NUMA_INFO.get() else {
+        return NodeId::first();
+    };
+    info.memory_nodes
+        .iter()
+        .find(|(range, _)| range.contains(&paddr))
+        .map_or(NodeId::first(), |(_, node)| *node)
+}
+
+/// Splits a page-aligned physical address range into the sub-ranges that
+/// each belong to a single node.
+///
+/// The sub-ranges are yielded in ascending order along with their nodes.
+pub fn split_range_by_node(range: Range<Paddr>) -> impl Iterator<Item = (Range<Paddr>, NodeId)> {
+    let mut start = range.start;
+    core::iter::from_fn(move || {
+        if start >= range.end {
+            return None;
+        }
+
+        let boundaries = NUMA_INFO
+            .get()
+            .into_iter()
+            .flat_map(|info| info.memory_nodes.iter())
+            .flat_map(|(node_range, _)| [node_range.start, node_range.end]);
+        let end = boundaries
+            .map(|boundary| boundary.align_down(PAGE_SIZE))
+            .filter(|&boundary| boundary > start && boundary < range.end)
+            .min()
+            .unwrap_or(range.end);
+
+        let sub_range = start..end;
+        start = end;
+        Some((sub_range.clone(), node_of_paddr(sub_range.start)))
+    })
+}
@@ -4,7 +4,9 @@
 
 mod guard;
 mod mutex;
+mod pi_mutex;
 mod rcu;
+mod ring;
 mod rwarc;
 mod rwlock;
 mod rwmutex;
@@ -15,7 +17,9 @@ pub(crate) use self::rcu::finish_grace_period;
 pub use self::{
     guard::{GuardTransfer, LocalIrqDisabled, PreemptDisabled, SpinGuardian, WriteIrqDisabled},
     mutex::{ArcMutexGuard, Mutex, MutexGuard},
+    pi_mutex::{PiMutex, PiMutexGuard},
     rcu::{non_null, Rcu, RcuDrop, RcuOption, RcuOptionReadGuard, RcuReadGuard},
+    ring::RingBuffer,
     rwarc::{RoArc, RwArc},
     rwlock::{
         ArcRwLockReadGuard, ArcRwLockUpgradeableGuard, ArcRwLockWriteGuard, RwLock,
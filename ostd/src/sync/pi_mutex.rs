@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use core::{
+    cell::UnsafeCell,
+    cmp::Ordering,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use super::{LocalIrqDisabled, SpinLock, Waiter, Waker};
+use crate::task::{scheduler, Task};
+
+/// A mutex with priority inheritance.
+///
+/// When a task blocks on a [`PiMutex`] held by a task with a lower priority,
+/// the holder temporarily inherits the priority of the blocked task until it
+/// releases the mutex. This bounds the time that a high-priority task may be
+/// delayed by a lower-priority one (i.e., priority inversion).
+///
+/// The priority is compared and lent through the methods of the injected
+/// [`Scheduler`]. Without a scheduler supporting them, a [`PiMutex`] behaves
+/// like an ordinary [`Mutex`].
+///
+/// The inheritance is transitive: if the holder is itself blocked on another
+/// [`PiMutex`], the holder of the latter is boosted as well, and so on along
+/// the chain of the blocked tasks.
+///
+/// [`Scheduler`]: crate::task::scheduler::Scheduler
+/// [`Mutex`]: super::Mutex
+pub struct PiMutex<T: ?Sized> {
+    state: SpinLock<PiMutexState, LocalIrqDisabled>,
+    val: UnsafeCell<T>,
+}
+
+/// The mutexes that the tasks are blocked on.
+///
+/// The keys are the addresses of the tasks, and the values are the identifiers
+/// of the mutexes (see [`PiMutex::lock_id`]). The lock also serializes the
+/// walks along the chains of the blocked tasks.
+static BLOCKED_ON: SpinLock<BTreeMap<usize, usize>, LocalIrqDisabled> =
+    SpinLock::new(BTreeMap::new());
+
+/// The maximum number of the mutexes in a chain that are walked.
+///
+/// This bounds the walk if the tasks in the chain deadlock.
+const MAX_CHAIN_DEPTH: usize = 1024;
+
+/// Walks the chain of the tasks that block `task` directly or indirectly.
+///
+/// For each task in the chain that is blocked on a [`PiMutex`], `f` is called
+/// with the holder of the mutex, the identifier of the mutex, and the blocked
+/// task, in that order. It is called with the state of the mutex locked, so
+/// the holder cannot release the mutex in the meantime.
+fn walk_blocking_chain(
+    blocked_on: &BTreeMap<usize, usize>,
+    task: &Arc<Task>,
+    mut f: impl FnMut(&Arc<Task>, usize, &Arc<Task>),
+) {
+    let mut task = task.clone();
+    for _ in 0..MAX_CHAIN_DEPTH {
+        let Some(&lock_id) = blocked_on.get(&task_id(&task)) else {
+            return;
+        };
+        // SAFETY: The task is blocked in `PiMutex::lock`, which borrows the
+        // mutex. It cannot return before removing its entry in `BLOCKED_ON`,
+        // which is locked by the caller, so the mutex is still alive.
+        let state_lock = unsafe { &*(lock_id as *const SpinLock<PiMutexState, LocalIrqDisabled>) };
+        let state = state_lock.lock();
+        let Some(owner) = state.owner.clone() else {
+            return;
+        };
+        f(&owner, lock_id, &task);
+        drop(state);
+
+        task = owner;
+    }
+}
+
+fn task_id(task: &Arc<Task>) -> usize {
+    Arc::as_ptr(task) as usize
+}
+
+struct PiMutexState {
+    is_locked: bool,
+    // The holder of the mutex. It can be `None` if the mutex is held outside
+    // of any task context (e.g., during the boot).
+    owner: Option<Arc<Task>>,
+    waiters: VecDeque<Arc<Waker>>,
+}
+
+impl PiMutexState {
+    /// Returns the position of the waiter with the highest priority.
+    ///
+    /// Waiters with the same priority are served in FIFO order.
+    fn top_waiter(&self) -> Option<usize> {
+        let mut top: Option<usize> = None;
+        for (i, waker) in self.waiters.iter().enumerate() {
+            let is_higher = top.is_none_or(|top| {
+                scheduler::cmp_priority(waker.task(), self.waiters[top].task()) == Ordering::Greater
+            });
+            if is_higher {
+                top = Some(i);
+            }
+        }
+        top
+    }
+}
+
+impl<T> PiMutex<T> {
+    /// Creates a new mutex.
+    pub const fn new(val: T) -> Self {
+        Self {
+            state: SpinLock::new(PiMutexState {
+                is_locked: false,
+                owner: None,
+                waiters: VecDeque::new(),
+            }),
+            val: UnsafeCell::new(val),
+        }
+    }
+}
+
+impl<T: ?Sized> PiMutex<T> {
+    /// Acquires the mutex.
+    ///
+    /// This method runs in a block way until the mutex can be acquired. While
+    /// blocking, the priority of the current task is lent to the holder.
+    #[track_caller]
+    pub fn lock(&self) -> PiMutexGuard<T> {
+        if let Some(guard) = self.try_lock() {
+            return guard;
+        }
+
+        let (waiter, waker) = Waiter::new_pair();
+        let current_id = task_id(waiter.task());
+        loop {
+            {
+                let mut blocked_on = BLOCKED_ON.lock();
+                let mut state = self.state.lock();
+                if !state.is_locked {
+                    blocked_on.remove(&current_id);
+                    state.waiters.retain(|queued| !Arc::ptr_eq(queued, &waker));
+                    self.acquire_lock(&mut state, Some(waiter.task().clone()));
+                    // SAFETY: The lock is successfully acquired.
+                    return unsafe { PiMutexGuard::new(self) };
+                }
+
+                if !state
+                    .waiters
+                    .iter()
+                    .any(|queued| Arc::ptr_eq(queued, &waker))
+                {
+                    state.waiters.push_back(waker.clone());
+                }
+                blocked_on.insert(current_id, self.lock_id());
+                drop(state);
+
+                // The priority is lent to the holder, and then to the holders
+                // of the mutexes that the holder is blocked on, and so on.
+                walk_blocking_chain(&blocked_on, waiter.task(), |owner, lock_id, task| {
+                    scheduler::inherit_priority(owner, lock_id, task);
+                });
+            }
+
+            waiter.wait();
+        }
+    }
+
+    /// Tries to acquire the mutex immediately.
+    pub fn try_lock(&self) -> Option<PiMutexGuard<T>> {
+        let mut state = self.state.lock();
+        if state.is_locked {
+            return None;
+        }
+
+        let owner = Task::current().map(|current| current.cloned());
+        self.acquire_lock(&mut state, owner);
+        // SAFETY: The lock is successfully acquired.
+        Some(unsafe { PiMutexGuard::new(self) })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// This method is zero-cost: By holding a mutable reference to the lock, the compiler has
+    /// already statically guaranteed that access to the data is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.val.get_mut()
+    }
+
+    fn acquire_lock(&self, state: &mut PiMutexState, owner: Option<Arc<Task>>) {
+        state.is_locked = true;
+        state.owner = owner;
+
+        // The tasks still waiting now lend their priorities to the new owner.
+        if let (Some(owner), Some(top)) = (state.owner.as_ref(), state.top_waiter()) {
+            scheduler::inherit_priority(owner, self.lock_id(), state.waiters[top].task());
+        }
+    }
+
+    /// Releases the mutex, restores the priority of the holder, and wakes up
+    /// the waiter with the highest priority.
+    fn unlock(&self) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.is_locked = false;
+            if let Some(owner) = state.owner.take() {
+                scheduler::revoke_priority(&owner, self.lock_id());
+            }
+            state.top_waiter().and_then(|top| state.waiters.remove(top))
+        };
+
+        if let Some(waker) = waker {
+            waker.wake_up();
+        }
+    }
+
+    /// Returns the identifier of the mutex used when lending priorities.
+    fn lock_id(&self) -> usize {
+        &self.state as *const _ as usize
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.val, f)
+    }
+}
+
+unsafe impl<T: ?Sized + Send> Send for PiMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for PiMutex<T> {}
+
+/// A guard that provides exclusive access to the data protected by a [`PiMutex`].
+#[clippy::has_significant_drop]
+#[must_use]
+pub struct PiMutexGuard<'a, T: ?Sized> {
+    mutex: &'a PiMutex<T>,
+}
+
+impl<'a, T: ?Sized> PiMutexGuard<'a, T> {
+    /// # Safety
+    ///
+    /// The caller must ensure that the given reference of [`PiMutex`] lock has been successfully
+    /// acquired in the current context. When the created [`PiMutexGuard`] is dropped, it will
+    /// unlock the [`PiMutex`].
+    unsafe fn new(mutex: &'a PiMutex<T>) -> PiMutexGuard<'a, T> {
+        PiMutexGuard { mutex }
+    }
+}
+
+impl<T: ?Sized> Deref for PiMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.val.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for PiMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.val.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for PiMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> !Send for PiMutexGuard<'_, T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for PiMutexGuard<'_, T> {}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{prelude::*, task::TaskOptions};
+
+    #[ktest]
+    fn lock_and_try_lock() {
+        let mutex = PiMutex::new(0);
+
+        let mut guard = mutex.lock();
+        *guard += 1;
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(*mutex.try_lock().unwrap(), 1);
+    }
+
+    // The lending and revoking of the priorities are up to the scheduler, which is tested in
+    // the kernel. Here the holder and the waiter are checked to be tracked correctly.
+    #[ktest]
+    fn holder_and_waiter() {
+        let mutex = Arc::new(PiMutex::new(0));
+        let mutex_cloned = mutex.clone();
+        let is_done = Arc::new(AtomicBool::new(false));
+        let is_done_cloned = is_done.clone();
+
+        let mut guard = mutex.lock();
+        let holder = Task::current().unwrap().cloned();
+        assert!(Arc::ptr_eq(
+            mutex.state.lock().owner.as_ref().unwrap(),
+            &holder
+        ));
+
+        TaskOptions::new(move || {
+            *mutex_cloned.lock() += 1;
+            is_done_cloned.store(true, Ordering::Relaxed);
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+
+        while mutex.state.lock().waiters.is_empty() {
+            Task::yield_now();
+        }
+        *guard += 1;
+        drop(guard);
+
+        // The waiter is woken up and takes the mutex over.
+        while !is_done.load(Ordering::Relaxed) {
+            Task::yield_now();
+        }
+        let state = mutex.state.lock();
+        assert!(!state.is_locked);
+        assert!(state.owner.is_none());
+        assert!(state.waiters.is_empty());
+        drop(state);
+        assert_eq!(*mutex.lock(), 2);
+    }
+
+    fn is_blocked(task: &Arc<Task>) -> bool {
+        BLOCKED_ON.lock().contains_key(&task_id(task))
+    }
+
+    // The priority is lent along the chain: the top task blocks on a mutex held by the middle
+    // task, which blocks on a mutex held by the current task.
+    #[ktest]
+    fn blocking_chain() {
+        let outer = Arc::new(PiMutex::new(0));
+        let inner = Arc::new(PiMutex::new(0));
+        let nr_done = Arc::new(AtomicUsize::new(0));
+
+        let outer_guard = outer.lock();
+        let holder = Task::current().unwrap().cloned();
+
+        let (outer_cloned, inner_cloned, nr_done_cloned) =
+            (outer.clone(), inner.clone(), nr_done.clone());
+        let middle = TaskOptions::new(move || {
+            let mut inner_guard = inner_cloned.lock();
+            *outer_cloned.lock() += 1;
+            *inner_guard += 1;
+            drop(inner_guard);
+            nr_done_cloned.fetch_add(1, Ordering::Relaxed);
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+        while !is_blocked(&middle) {
+            Task::yield_now();
+        }
+
+        let (inner_cloned, nr_done_cloned) = (inner.clone(), nr_done.clone());
+        let top = TaskOptions::new(move || {
+            *inner_cloned.lock() += 1;
+            nr_done_cloned.fetch_add(1, Ordering::Relaxed);
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+        while !is_blocked(&top) {
+            Task::yield_now();
+        }
+
+        let mut chain = Vec::new();
+        walk_blocking_chain(&BLOCKED_ON.lock(), &top, |owner, lock_id, task| {
+            chain.push((task_id(owner), lock_id, task_id(task)));
+        });
+        assert_eq!(
+            chain,
+            [
+                (task_id(&middle), inner.lock_id(), task_id(&top)),
+                (task_id(&holder), outer.lock_id(), task_id(&middle)),
+            ]
+        );
+
+        // The chain is resolved once the current task releases its mutex.
+        drop(outer_guard);
+        while nr_done.load(Ordering::Relaxed) < 2 {
+            Task::yield_now();
+        }
+        assert!(!is_blocked(&middle));
+        assert!(!is_blocked(&top));
+        assert_eq!(*outer.lock(), 1);
+        assert_eq!(*inner.lock(), 2);
+    }
+}
@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::boxed::Box;
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A bounded, lock-free ring buffer.
+///
+/// The ring buffer allows multiple producers and multiple consumers, so it can serve as an SPSC
+/// or an MPSC queue as well. No operation takes a lock or waits for another operation to finish,
+/// so items can be pushed in interrupt handlers and popped in threads (or vice versa) without
+/// disabling local IRQs.
+///
+/// The capacity must be a power of two. In the overwrite mode, pushing an item to a full ring
+/// buffer drops the oldest item instead of failing, which suits buffers of events or logs where
+/// the latest items matter the most.
+///
+/// The implementation follows Dmitry Vyukov's bounded MPMC queue. Each slot has a sequence
+/// number that tells whether the slot is ready to be written or read in the current lap.
+///
+/// # Examples
+///
+/// ```
+/// use ostd::sync::RingBuffer;
+///
+/// let ring = RingBuffer::new(4);
+/// for i in 0..4 {
+///     ring.push(i).unwrap();
+/// }
+/// assert_eq!(ring.push(4), Err(4));
+/// assert_eq!(ring.pop(), Some(0));
+///
+/// let ring = RingBuffer::new_overwrite(4);
+/// for i in 0..5 {
+///     ring.push(i).unwrap();
+/// }
+/// assert_eq!(ring.pop(), Some(1));
+/// ```
+pub struct RingBuffer<T> {
+    /// The position of the next item to pop.
+    head: CachePadded<AtomicUsize>,
+    /// The position of the next item to push.
+    tail: CachePadded<AtomicUsize>,
+    slots: Box<[Slot<T>]>,
+    is_overwrite: bool,
+}
+
+struct Slot<T> {
+    /// The sequence number.
+    ///
+    /// For a slot at position `pos`, the slot is ready to be written if the sequence number is
+    /// `pos`, and it is ready to be read if the sequence number is `pos + 1`.
+    seq: AtomicUsize,
+    item: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Pads and aligns a value to the size of a cache line.
+///
+/// Placing the head and the tail in different cache lines avoids false sharing between the
+/// producers and the consumers.
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+// SAFETY: The items are moved into and out of the ring buffer, possibly on different CPUs, so
+// `T: Send` is enough. The sequence numbers ensure that a slot is accessed by at most one
+// producer or one consumer at a time.
+unsafe impl<T: Send> Send for RingBuffer<T> {}
+// SAFETY: See above. No `&T` is ever shared via `&RingBuffer<T>`.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> RingBuffer<T> {
+    /// Creates a ring buffer with the given capacity.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the capacity is not a power of two.
+    pub fn new(capacity: usize) -> Self {
+        Self::new_with_mode(capacity, false)
+    }
+
+    /// Creates a ring buffer with the given capacity in the overwrite mode.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the capacity is not a power of two.
+    pub fn new_overwrite(capacity: usize) -> Self {
+        Self::new_with_mode(capacity, true)
+    }
+
+    fn new_with_mode(capacity: usize, is_overwrite: bool) -> Self {
+        assert!(
+            capacity.is_power_of_two(),
+            "the capacity must be a power of two"
+        );
+
+        let slots = (0..capacity)
+            .map(|pos| Slot {
+                seq: AtomicUsize::new(pos),
+                item: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        Self {
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+            slots,
+            is_overwrite,
+        }
+    }
+
+    /// Returns the capacity.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of items.
+    ///
+    /// The result may be outdated as soon as it is returned if the ring buffer is accessed
+    /// concurrently.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            let head = self.head.load(Ordering::Acquire);
+            if self.tail.load(Ordering::Acquire) == tail {
+                return tail.wrapping_sub(head).min(self.capacity());
+            }
+        }
+    }
+
+    /// Returns whether the ring buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the ring buffer is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Pushes an item.
+    ///
+    /// If the ring buffer is full, the item is returned as an error. In the overwrite mode, the
+    /// oldest item is dropped to make room for the new item instead. But if the oldest item is
+    /// still being pushed by a producer that has been interrupted, the new item is returned as an
+    /// error, since the producer cannot be waited for in the interrupt context.
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let item = match self.try_push(item) {
+            Ok(()) => return Ok(()),
+            Err(item) if self.is_overwrite => item,
+            Err(item) => return Err(item),
+        };
+
+        // Drop the oldest items until there is room for the new item.
+        let mut item = item;
+        loop {
+            if self.pop().is_none() {
+                return Err(item);
+            }
+            match self.try_push(item) {
+                Ok(()) => return Ok(()),
+                Err(returned) => item = returned,
+            }
+        }
+    }
+
+    fn try_push(&self, item: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & (self.capacity() - 1)];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos as isize) {
+                0 => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: Claiming the position gives us exclusive access to the slot
+                        // until the sequence number is updated below.
+                        unsafe { (*slot.item.get()).write(item) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot has not been popped in the last lap, so the ring buffer is full.
+                diff if diff < 0 => return Err(item),
+                // Another producer has claimed the position.
+                _ => pos = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Pops the oldest item.
+    ///
+    /// Returns `None` if the ring buffer is empty, or if the oldest item is still being pushed.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & (self.capacity() - 1)];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos.wrapping_add(1) as isize) {
+                0 => match self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: Claiming the position gives us exclusive access to the slot,
+                        // which has been initialized by the producer, until the sequence number
+                        // is updated below.
+                        let item = unsafe { (*slot.item.get()).assume_init_read() };
+                        slot.seq
+                            .store(pos.wrapping_add(self.capacity()), Ordering::Release);
+                        return Some(item);
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot has not been pushed in this lap, so the ring buffer is empty.
+                diff if diff < 0 => return None,
+                // Another consumer has claimed the position.
+                _ => pos = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Drops all the items.
+    pub fn clear(&self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[ktest]
+    fn push_and_pop() {
+        let ring = RingBuffer::new(4);
+        assert!(ring.is_empty());
+
+        for i in 0..4 {
+            ring.push(i).unwrap();
+        }
+        assert!(ring.is_full());
+        assert_eq!(ring.push(4), Err(4));
+
+        for i in 0..4 {
+            assert_eq!(ring.pop(), Some(i));
+        }
+        assert_eq!(ring.pop(), None);
+
+        // Wrap around many times.
+        for i in 0..100 {
+            ring.push(i).unwrap();
+            assert_eq!(ring.len(), 1);
+            assert_eq!(ring.pop(), Some(i));
+        }
+    }
+
+    #[ktest]
+    fn overwrite() {
+        let ring = RingBuffer::new_overwrite(4);
+        for i in 0..10 {
+            ring.push(i).unwrap();
+        }
+        assert_eq!(ring.len(), 4);
+
+        for i in 6..10 {
+            assert_eq!(ring.pop(), Some(i));
+        }
+        assert!(ring.is_empty());
+    }
+
+    #[ktest]
+    fn drop_items() {
+        let item = Arc::new(());
+        let ring = RingBuffer::new_overwrite(2);
+        for _ in 0..3 {
+            ring.push(item.clone()).unwrap();
+        }
+        assert_eq!(Arc::strong_count(&item), 3);
+
+        drop(ring);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+}
@@ -268,6 +268,11 @@ impl Waker {
         true
     }
 
+    /// Returns the task that the waker will attempt to wake up.
+    pub(super) fn task(&self) -> &Arc<Task> {
+        &self.task
+    }
+
     #[track_caller]
     fn do_wait(&self) {
         while !self.has_woken.swap(false, Ordering::Acquire) {
@@ -14,8 +14,9 @@ use core::{
     borrow::Borrow,
     cell::{Cell, SyncUnsafeCell},
     ops::Deref,
+    panic::Location,
     ptr::NonNull,
-    sync::atomic::AtomicBool,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
 };
 
 use kernel_stack::KernelStack;
@@ -62,6 +63,12 @@ pub struct Task {
     switched_to_cpu: AtomicBool,
 
     schedule_info: TaskScheduleInfo,
+
+    /// The location where the task is blocked, or null if it is not blocked.
+    wait_location: AtomicPtr<Location<'static>>,
+
+    #[cfg(target_arch = "x86_64")]
+    patch_state: crate::arch::livepatch::TaskPatchState,
 }
 
 impl Task {
@@ -122,6 +129,33 @@ impl Task {
         &self.schedule_info
     }
 
+    /// Returns the location where the task is blocked.
+    ///
+    /// The location is the caller of the blocking method (e.g.,
+    /// [`WaitQueue::wait_until`]) that is annotated with `#[track_caller]`
+    /// all the way down. It is `None` if the task is not blocked.
+    ///
+    /// [`WaitQueue::wait_until`]: crate::sync::WaitQueue::wait_until
+    pub fn wait_location(&self) -> Option<&'static Location<'static>> {
+        let ptr = self.wait_location.load(Ordering::Relaxed);
+        // SAFETY: The pointer is either null or obtained from a `&'static Location<'static>` in
+        // `set_wait_location`.
+        unsafe { ptr.as_ref() }
+    }
+
+    pub(crate) fn set_wait_location(&self, location: Option<&'static Location<'static>>) {
+        let ptr = location.map_or(core::ptr::null_mut(), |location| {
+            location as *const Location<'static> as *mut Location<'static>
+        });
+        self.wait_location.store(ptr, Ordering::Relaxed);
+    }
+
+    /// Returns the live patching state of this task.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn patch_state(&self) -> &crate::arch::livepatch::TaskPatchState {
+        &self.patch_state
+    }
+
     /// Returns the user context of this task, if it has.
     pub fn user_ctx(&self) -> Option<&Arc<UserContext>> {
         if self.user_ctx.is_some() {
@@ -263,6 +297,9 @@ impl TaskOptions {
                 cpu: AtomicCpuId::default(),
             },
             switched_to_cpu: AtomicBool::new(false),
+            wait_location: AtomicPtr::new(core::ptr::null_mut()),
+            #[cfg(target_arch = "x86_64")]
+            patch_state: crate::arch::livepatch::TaskPatchState::new(),
         };
 
         Ok(new_task)
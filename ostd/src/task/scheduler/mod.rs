@@ -8,6 +8,8 @@
 mod fifo_scheduler;
 pub mod info;
 
+use core::{cmp::Ordering, panic::Location};
+
 use spin::Once;
 
 use super::{preempt::cpu_local, processor, Task};
@@ -50,6 +52,33 @@ pub trait Scheduler<T = Task>: Sync + Send {
 
     /// Gets a mutable access to the local runqueue of the current CPU core.
     fn local_mut_rq_with(&self, f: &mut dyn FnMut(&mut dyn LocalRunQueue<T>));
+
+    /// Compares the effective priorities of two tasks.
+    ///
+    /// It returns [`Ordering::Greater`] if `a` has a higher priority than `b`.
+    ///
+    /// The default implementation considers all tasks as equal.
+    fn cmp_priority(&self, _a: &T, _b: &T) -> Ordering {
+        Ordering::Equal
+    }
+
+    /// Lends the effective priority of `donor` to `task`, on behalf of the
+    /// priority-inheritance lock identified by `lock_id`.
+    ///
+    /// The effective priority of `task` should be no lower than any priority
+    /// lent to it until the priority is revoked by [`Self::revoke_priority`].
+    ///
+    /// If the `current` of a CPU needs to be preempted as a result, this method
+    /// returns the id of that CPU. The default implementation does nothing.
+    fn inherit_priority(&self, _task: &Arc<T>, _lock_id: usize, _donor: &T) -> Option<CpuId> {
+        None
+    }
+
+    /// Revokes the priority lent to `task` on behalf of the lock identified by
+    /// `lock_id`.
+    ///
+    /// The default implementation does nothing.
+    fn revoke_priority(&self, _task: &Arc<T>, _lock_id: usize) {}
 }
 
 /// The _local_ view of a per-CPU runqueue.
@@ -125,6 +154,12 @@ where
     let mut current = None;
     let mut is_first_try = true;
 
+    // Record where the task is blocked, which is useful for diagnosing stuck tasks.
+    let current_task = Task::current();
+    if let Some(task) = current_task.as_ref() {
+        task.set_wait_location(Some(Location::caller()));
+    }
+
     reschedule(|local_rq: &mut dyn LocalRunQueue| {
         if is_first_try {
             if has_woken() {
@@ -154,6 +189,10 @@ where
         is_first_try = false;
         ReschedAction::Retry
     });
+
+    if let Some(task) = current_task.as_ref() {
+        task.set_wait_location(None);
+    }
 }
 
 /// Unblocks a target task.
@@ -167,6 +206,36 @@ pub(crate) fn unpark_target(runnable: Arc<Task>) {
     }
 }
 
+/// Compares the effective priorities of two tasks.
+///
+/// See [`Scheduler::cmp_priority`] for details.
+pub(crate) fn cmp_priority(a: &Task, b: &Task) -> Ordering {
+    SCHEDULER
+        .get()
+        .map_or(Ordering::Equal, |scheduler| scheduler.cmp_priority(a, b))
+}
+
+/// Lends the effective priority of `donor` to `task`.
+///
+/// See [`Scheduler::inherit_priority`] for details.
+pub(crate) fn inherit_priority(task: &Arc<Task>, lock_id: usize, donor: &Task) {
+    let Some(scheduler) = SCHEDULER.get() else {
+        return;
+    };
+    if let Some(preempt_cpu_id) = scheduler.inherit_priority(task, lock_id, donor) {
+        set_need_preempt(preempt_cpu_id);
+    }
+}
+
+/// Revokes the priority lent to `task`.
+///
+/// See [`Scheduler::revoke_priority`] for details.
+pub(crate) fn revoke_priority(task: &Arc<Task>, lock_id: usize) {
+    if let Some(scheduler) = SCHEDULER.get() {
+        scheduler.revoke_priority(task, lock_id);
+    }
+}
+
 /// Enqueues a newly built task.
 ///
 /// Note that the new task is not guaranteed to run at once.
@@ -6,6 +6,7 @@ use super::{disable_local, DisabledLocalIrqGuard};
 use crate::{arch::irq::IRQ_LIST, cpu_local_cell, task::disable_preempt, trap::TrapFrame};
 
 static BOTTOM_HALF_HANDLER: Once<fn(DisabledLocalIrqGuard) -> DisabledLocalIrqGuard> = Once::new();
+static IRQ_SAMPLER: Once<fn(usize)> = Once::new();
 
 /// Registers a function to the interrupt bottom half execution.
 ///
@@ -26,6 +27,18 @@ pub fn register_bottom_half_handler(func: fn(DisabledLocalIrqGuard) -> DisabledL
     BOTTOM_HALF_HANDLER.call_once(|| func);
 }
 
+/// Registers a function to sample every interrupt.
+///
+/// The sampler is called with the IRQ number following the execution of the
+/// top half, with interrupts disabled. It is meant for cheap bookkeeping, such
+/// as collecting entropy from the arrival times of interrupts, so it should
+/// return quickly.
+///
+/// This function can only be registered once. Subsequent calls will do nothing.
+pub fn register_irq_sampler(func: fn(usize)) {
+    IRQ_SAMPLER.call_once(|| func);
+}
+
 fn process_top_half(trap_frame: &TrapFrame, irq_number: usize) {
     let irq_line = IRQ_LIST.get().unwrap().get(irq_number).unwrap();
     let callback_functions = irq_line.callback_list();
@@ -74,6 +87,10 @@ pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame, irq_number: us
     process_top_half(trap_frame, irq_number);
     crate::arch::interrupts_ack(irq_number);
 
+    if let Some(sampler) = IRQ_SAMPLER.get() {
+        sampler(irq_number);
+    }
+
     if INTERRUPT_NESTED_LEVEL.load() == 1 {
         process_bottom_half();
     }
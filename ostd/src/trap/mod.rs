@@ -5,7 +5,7 @@
 mod handler;
 mod irq;
 
-pub use handler::{in_interrupt_context, register_bottom_half_handler};
+pub use handler::{in_interrupt_context, register_bottom_half_handler, register_irq_sampler};
 
 pub(crate) use self::handler::call_irq_callback_functions;
 pub use self::irq::{disable_local, DisabledLocalIrqGuard, IrqCallbackFunction, IrqLine};
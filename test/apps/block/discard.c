@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdint.h>
+#include <unistd.h>
+#include <linux/fs.h>
+#include <sys/ioctl.h>
+
+#include "../network/test.h"
+
+// The device is mounted, so only the ranges that do not touch the data are used.
+#define DEV_FILE "/dev/vext2"
+#define QUEUE_DIR "/sys/class/block/vext2/queue"
+
+static int dev_fd;
+static uint64_t dev_size;
+static int discard_supported;
+static char buf[64];
+
+static int read_number_attr(const char *path, unsigned long *value)
+{
+	ssize_t len;
+	int fd;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0) {
+		return -1;
+	}
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (len < 0) {
+		return -1;
+	}
+	buf[len] = '\0';
+
+	return sscanf(buf, "%lu", value) == 1 ? 0 : -1;
+}
+
+FN_SETUP(open)
+{
+	dev_fd = CHECK(open(DEV_FILE, O_RDWR));
+	CHECK(ioctl(dev_fd, BLKGETSIZE64, &dev_size));
+}
+END_SETUP()
+
+FN_SETUP(discard_limit)
+{
+	unsigned long max_bytes;
+
+	CHECK(read_number_attr(QUEUE_DIR "/discard_max_bytes", &max_bytes));
+	discard_supported = max_bytes != 0;
+}
+END_SETUP()
+
+FN_TEST(queue_limits)
+{
+	unsigned long value;
+
+	TEST_RES(read_number_attr(QUEUE_DIR "/discard_max_bytes", &value),
+		 _ret == 0 && (value & 511) == 0);
+	TEST_RES(read_number_attr(QUEUE_DIR "/write_zeroes_max_bytes", &value),
+		 _ret == 0 && (value & 511) == 0);
+}
+END_TEST()
+
+FN_TEST(discard)
+{
+	uint64_t range[2];
+
+	if (!discard_supported) {
+		range[0] = 0;
+		range[1] = 512;
+		TEST_ERRNO(ioctl(dev_fd, BLKDISCARD, range), EOPNOTSUPP);
+	} else {
+		range[0] = 1;
+		range[1] = 512;
+		TEST_ERRNO(ioctl(dev_fd, BLKDISCARD, range), EINVAL);
+
+		range[0] = 0;
+		range[1] = 511;
+		TEST_ERRNO(ioctl(dev_fd, BLKDISCARD, range), EINVAL);
+
+		range[0] = dev_size;
+		range[1] = 512;
+		TEST_ERRNO(ioctl(dev_fd, BLKDISCARD, range), EINVAL);
+
+		range[0] = 512;
+		range[1] = UINT64_MAX - 511;
+		TEST_ERRNO(ioctl(dev_fd, BLKDISCARD, range), EINVAL);
+	}
+}
+END_TEST()
+
+FN_TEST(zeroout_invalid_range)
+{
+	uint64_t range[2];
+
+	range[0] = 1;
+	range[1] = 512;
+	TEST_ERRNO(ioctl(dev_fd, BLKZEROOUT, range), EINVAL);
+
+	range[0] = dev_size - 512;
+	range[1] = 1024;
+	TEST_ERRNO(ioctl(dev_fd, BLKZEROOUT, range), EINVAL);
+}
+END_TEST()
+
+FN_TEST(empty_range)
+{
+	uint64_t range[2];
+
+	range[0] = 0;
+	range[1] = 0;
+	TEST_ERRNO(ioctl(dev_fd, BLKZEROOUT, range), EINVAL);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(dev_fd));
+}
+END_SETUP()
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include <linux/fs.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define DEV_FILE "/dev/vext2"
+#define SYSFS_DIR "/sys/class/block/vext2"
+#define BLOCK_MAJOR 259
+
+static int dev_fd;
+static char buf[64];
+
+static unsigned long read_number_attr(const char *path)
+{
+	unsigned long value;
+	ssize_t len;
+	int fd;
+
+	fd = CHECK(open(path, O_RDONLY));
+	len = CHECK(read(fd, buf, sizeof(buf) - 1));
+	buf[len] = '\0';
+	CHECK(close(fd));
+
+	if (sscanf(buf, "%lu", &value) != 1) {
+		return 0;
+	}
+	return value;
+}
+
+FN_SETUP(open)
+{
+	dev_fd = CHECK(open(DEV_FILE, O_RDONLY));
+}
+END_SETUP()
+
+FN_TEST(device_file)
+{
+	struct stat stat_buf;
+	unsigned int dev_major, dev_minor;
+	int fd;
+
+	TEST_RES(stat(DEV_FILE, &stat_buf),
+		 S_ISBLK(stat_buf.st_mode) &&
+			 major(stat_buf.st_rdev) == BLOCK_MAJOR);
+
+	fd = TEST_SUCC(open(SYSFS_DIR "/dev", O_RDONLY));
+	TEST_RES(read(fd, buf, sizeof(buf) - 1),
+		 _ret > 0 &&
+			 sscanf(buf, "%u:%u", &dev_major, &dev_minor) == 2 &&
+			 dev_major == BLOCK_MAJOR &&
+			 dev_minor == minor(stat_buf.st_rdev));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(size)
+{
+	unsigned long sectors = read_number_attr(SYSFS_DIR "/size");
+	unsigned long nr_sectors;
+	uint64_t size;
+	int sector_size;
+
+	TEST_RES(ioctl(dev_fd, BLKGETSIZE64, &size),
+		 size == (uint64_t)sectors * 512);
+	TEST_RES(ioctl(dev_fd, BLKGETSIZE, &nr_sectors),
+		 nr_sectors == sectors);
+	TEST_RES(ioctl(dev_fd, BLKSSZGET, &sector_size), sector_size == 512);
+}
+END_TEST()
+
+FN_TEST(rescan)
+{
+	// The ext2 image has no partition table.
+	TEST_SUCC(ioctl(dev_fd, BLKRRPART));
+	TEST_ERRNO(access(DEV_FILE "p1", F_OK), ENOENT);
+	TEST_ERRNO(access(SYSFS_DIR "p1", F_OK), ENOENT);
+
+	// The device can be scanned again.
+	TEST_SUCC(ioctl(dev_fd, BLKRRPART));
+	TEST_SUCC(access(DEV_FILE, F_OK));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(dev_fd));
+}
+END_SETUP()
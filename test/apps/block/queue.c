@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define QUEUE_DIR "/sys/class/block/vext2/queue"
+#define STAT_FILE "/sys/class/block/vext2/stat"
+#define TEST_FILE "/ext2/block_queue_test"
+
+static char buf[256];
+
+static ssize_t read_attr(const char *path)
+{
+	int fd;
+	ssize_t len;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0) {
+		return -1;
+	}
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+
+	if (len >= 0) {
+		buf[len] = '\0';
+	}
+	return len;
+}
+
+static ssize_t write_attr(const char *path, const char *value)
+{
+	int fd;
+	ssize_t len;
+
+	fd = open(path, O_WRONLY);
+	if (fd < 0) {
+		return -1;
+	}
+	len = write(fd, value, strlen(value));
+	close(fd);
+
+	return len;
+}
+
+static unsigned long write_ios(void)
+{
+	unsigned long read_ios, read_merges, read_sectors, read_ticks;
+	unsigned long write_ios;
+
+	if (read_attr(STAT_FILE) < 0 ||
+	    sscanf(buf, "%lu %lu %lu %lu %lu", &read_ios, &read_merges,
+		   &read_sectors, &read_ticks, &write_ios) != 5) {
+		return 0;
+	}
+	return write_ios;
+}
+
+FN_TEST(scheduler)
+{
+	TEST_RES(read_attr(QUEUE_DIR "/scheduler"),
+		 strcmp(buf, "[mq-deadline] none\n") == 0);
+
+	TEST_SUCC(write_attr(QUEUE_DIR "/scheduler", "none\n"));
+	TEST_RES(read_attr(QUEUE_DIR "/scheduler"),
+		 strcmp(buf, "mq-deadline [none]\n") == 0);
+
+	TEST_ERRNO(write_attr(QUEUE_DIR "/scheduler", "bfq"), EIO);
+	TEST_RES(read_attr(QUEUE_DIR "/scheduler"),
+		 strcmp(buf, "mq-deadline [none]\n") == 0);
+
+	TEST_SUCC(write_attr(QUEUE_DIR "/scheduler", "mq-deadline"));
+	TEST_RES(read_attr(QUEUE_DIR "/scheduler"),
+		 strcmp(buf, "[mq-deadline] none\n") == 0);
+}
+END_TEST()
+
+FN_TEST(deadline_tunables)
+{
+	TEST_RES(read_attr(QUEUE_DIR "/iosched/read_expire"),
+		 strcmp(buf, "500\n") == 0);
+	TEST_RES(read_attr(QUEUE_DIR "/iosched/write_expire"),
+		 strcmp(buf, "5000\n") == 0);
+	TEST_RES(read_attr(QUEUE_DIR "/iosched/writes_starved"),
+		 strcmp(buf, "2\n") == 0);
+
+	TEST_SUCC(write_attr(QUEUE_DIR "/iosched/fifo_batch", "8"));
+	TEST_RES(read_attr(QUEUE_DIR "/iosched/fifo_batch"),
+		 strcmp(buf, "8\n") == 0);
+	TEST_ERRNO(write_attr(QUEUE_DIR "/iosched/fifo_batch", "-1"), EIO);
+	TEST_SUCC(write_attr(QUEUE_DIR "/iosched/fifo_batch", "16"));
+	TEST_RES(read_attr(QUEUE_DIR "/iosched/fifo_batch"),
+		 strcmp(buf, "16\n") == 0);
+}
+END_TEST()
+
+FN_TEST(stat)
+{
+	unsigned long nr_writes;
+	int fd;
+
+	TEST_RES(read_attr(QUEUE_DIR "/max_segments"), strlen(buf) > 1);
+
+	nr_writes = write_ios();
+	fd = TEST_SUCC(open(TEST_FILE, O_CREAT | O_RDWR | O_TRUNC, 0644));
+	memset(buf, 'a', sizeof(buf));
+	TEST_RES(write(fd, buf, sizeof(buf)), _ret == sizeof(buf));
+	TEST_SUCC(fsync(fd));
+	TEST_SUCC(close(fd));
+	TEST_RES(write_ios(), _ret > nr_writes);
+
+	TEST_SUCC(unlink(TEST_FILE));
+}
+END_TEST()
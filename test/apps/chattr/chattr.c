@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <linux/fs.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define BASE_DIR "/ext2/chattr_test"
+#define FILE_PATH BASE_DIR "/file"
+#define DIR_PATH BASE_DIR "/dir"
+#define RAMFS_FILE "/tmp/chattr_test"
+
+static int fd;
+
+static int get_flags(int fd)
+{
+	int flags;
+
+	if (ioctl(fd, FS_IOC_GETFLAGS, &flags) < 0) {
+		return -1;
+	}
+	return flags;
+}
+
+static int set_flags(int fd, int flags)
+{
+	return ioctl(fd, FS_IOC_SETFLAGS, &flags);
+}
+
+static int has_attribute(const char *path, __u64 attribute)
+{
+	struct statx buf;
+
+	if (statx(AT_FDCWD, path, 0, STATX_BASIC_STATS, &buf) < 0) {
+		return 0;
+	}
+	return (buf.stx_attributes_mask & attribute) &&
+	       (buf.stx_attributes & attribute);
+}
+
+FN_SETUP(files)
+{
+	CHECK(mkdir(BASE_DIR, 0755));
+	CHECK(mkdir(DIR_PATH, 0755));
+	fd = CHECK(open(FILE_PATH, O_CREAT | O_RDWR, 0644));
+	CHECK(write(fd, "data", 4));
+}
+END_SETUP()
+
+FN_TEST(get_and_set)
+{
+	int pipe_fds[2];
+
+	TEST_RES(get_flags(fd), _ret == 0);
+	TEST_ERRNO(set_flags(fd, FS_NOCOW_FL), EOPNOTSUPP);
+
+	TEST_SUCC(set_flags(fd, FS_APPEND_FL));
+	TEST_RES(get_flags(fd), _ret == FS_APPEND_FL);
+	TEST_RES(has_attribute(FILE_PATH, STATX_ATTR_APPEND), _ret);
+	TEST_SUCC(set_flags(fd, 0));
+	TEST_RES(has_attribute(FILE_PATH, STATX_ATTR_APPEND), !_ret);
+
+	TEST_SUCC(pipe(pipe_fds));
+	TEST_ERRNO(get_flags(pipe_fds[0]), ENOTTY);
+	TEST_SUCC(close(pipe_fds[0]));
+	TEST_SUCC(close(pipe_fds[1]));
+}
+END_TEST()
+
+FN_TEST(immutable_file)
+{
+	TEST_SUCC(set_flags(fd, FS_IMMUTABLE_FL));
+	TEST_RES(has_attribute(FILE_PATH, STATX_ATTR_IMMUTABLE), _ret);
+
+	TEST_ERRNO(open(FILE_PATH, O_WRONLY), EPERM);
+	TEST_ERRNO(open(FILE_PATH, O_RDONLY | O_TRUNC), EPERM);
+	TEST_ERRNO(truncate(FILE_PATH, 0), EPERM);
+	TEST_ERRNO(unlink(FILE_PATH), EPERM);
+	TEST_ERRNO(rename(FILE_PATH, BASE_DIR "/new"), EPERM);
+	TEST_ERRNO(link(FILE_PATH, BASE_DIR "/link"), EPERM);
+	TEST_SUCC(close(TEST_SUCC(open(FILE_PATH, O_RDONLY))));
+
+	TEST_SUCC(set_flags(fd, 0));
+	TEST_SUCC(close(TEST_SUCC(open(FILE_PATH, O_WRONLY))));
+}
+END_TEST()
+
+FN_TEST(append_only_file)
+{
+	struct stat stat_buf;
+	int append_fd;
+
+	TEST_SUCC(set_flags(fd, FS_APPEND_FL));
+
+	TEST_ERRNO(open(FILE_PATH, O_WRONLY), EPERM);
+	TEST_ERRNO(open(FILE_PATH, O_WRONLY | O_APPEND | O_TRUNC), EPERM);
+	TEST_ERRNO(truncate(FILE_PATH, 0), EPERM);
+	TEST_ERRNO(unlink(FILE_PATH), EPERM);
+	TEST_ERRNO(rename(FILE_PATH, BASE_DIR "/new"), EPERM);
+
+	append_fd = TEST_SUCC(open(FILE_PATH, O_WRONLY | O_APPEND));
+	TEST_RES(write(append_fd, "more", 4), _ret == 4);
+	TEST_RES(fstat(append_fd, &stat_buf), stat_buf.st_size == 8);
+	TEST_ERRNO(ftruncate(append_fd, 0), EPERM);
+	TEST_SUCC(close(append_fd));
+
+	TEST_SUCC(set_flags(fd, 0));
+}
+END_TEST()
+
+FN_TEST(immutable_dir)
+{
+	int dir_fd;
+
+	dir_fd = TEST_SUCC(open(DIR_PATH, O_RDONLY | O_DIRECTORY));
+	TEST_SUCC(close(TEST_SUCC(
+		open(DIR_PATH "/old", O_CREAT | O_WRONLY, 0644))));
+
+	TEST_SUCC(set_flags(dir_fd, FS_IMMUTABLE_FL));
+	TEST_ERRNO(open(DIR_PATH "/new", O_CREAT | O_WRONLY, 0644), EPERM);
+	TEST_ERRNO(mkdir(DIR_PATH "/new", 0755), EPERM);
+	TEST_ERRNO(unlink(DIR_PATH "/old"), EPERM);
+	TEST_ERRNO(rename(FILE_PATH, DIR_PATH "/new"), EPERM);
+
+	// The children of an append-only directory can be created, but not
+	// deleted.
+	TEST_SUCC(set_flags(dir_fd, FS_APPEND_FL));
+	TEST_SUCC(close(TEST_SUCC(
+		open(DIR_PATH "/new", O_CREAT | O_WRONLY, 0644))));
+	TEST_ERRNO(unlink(DIR_PATH "/new"), EPERM);
+	TEST_ERRNO(rename(DIR_PATH "/new", BASE_DIR "/new"), EPERM);
+
+	TEST_SUCC(set_flags(dir_fd, 0));
+	TEST_SUCC(unlink(DIR_PATH "/new"));
+	TEST_SUCC(unlink(DIR_PATH "/old"));
+	TEST_SUCC(close(dir_fd));
+}
+END_TEST()
+
+FN_TEST(ramfs)
+{
+	int ramfs_fd;
+
+	ramfs_fd = TEST_SUCC(open(RAMFS_FILE, O_CREAT | O_RDWR, 0644));
+	TEST_SUCC(set_flags(ramfs_fd, FS_IMMUTABLE_FL));
+	TEST_RES(get_flags(ramfs_fd), _ret == FS_IMMUTABLE_FL);
+	TEST_RES(has_attribute(RAMFS_FILE, STATX_ATTR_IMMUTABLE), _ret);
+	TEST_ERRNO(open(RAMFS_FILE, O_WRONLY), EPERM);
+	TEST_ERRNO(unlink(RAMFS_FILE), EPERM);
+
+	TEST_SUCC(set_flags(ramfs_fd, 0));
+	TEST_SUCC(close(ramfs_fd));
+	TEST_SUCC(unlink(RAMFS_FILE));
+}
+END_TEST()
+
+FN_TEST(unprivileged)
+{
+	int status;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		CHECK(setuid(65534));
+		if (set_flags(fd, FS_IMMUTABLE_FL) != -1 || errno != EPERM) {
+			_exit(1);
+		}
+		_exit(0);
+	}
+
+	TEST_RES(wait(&status), _ret == pid && WIFEXITED(status) &&
+					WEXITSTATUS(status) == 0);
+	TEST_RES(get_flags(fd), _ret == 0);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(fd));
+	CHECK(unlink(FILE_PATH));
+	CHECK(rmdir(DIR_PATH));
+	CHECK(rmdir(BASE_DIR));
+}
+END_SETUP()
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <time.h>
+#include <unistd.h>
+#include <sys/time.h>
+#include <sys/timex.h>
+
+#include "../network/test.h"
+
+#define FD_TO_CLOCKID(fd) ((~(clockid_t)(fd) << 3) | 3)
+
+// The real time is stepped forward by this amount and then restored.
+#define STEP_SECS 1000
+// The real time is slewed backwards by this amount.
+#define SLEW_USECS 100000
+// The real time is slewed at most by 500 parts per million.
+#define SLEW_MEASURE_USECS 200000
+#define SLEW_MIN_LAG_NSECS 50000
+
+#ifndef ADJ_ADJTIME
+#define ADJ_ADJTIME 0x8000
+#endif
+
+FN_TEST(invalid_args)
+{
+	struct timespec ts = { .tv_sec = 0, .tv_nsec = 0 };
+	int fd;
+
+	TEST_ERRNO(clock_settime(CLOCK_MONOTONIC, &ts), EINVAL);
+	TEST_ERRNO(clock_settime(CLOCK_BOOTTIME, &ts), EINVAL);
+
+	ts.tv_nsec = -1;
+	TEST_ERRNO(clock_settime(CLOCK_REALTIME, &ts), EINVAL);
+
+	// A file that is not a clock cannot be used as a dynamic clock.
+	fd = TEST_SUCC(open("/dev/null", O_RDONLY));
+	TEST_ERRNO(clock_gettime(FD_TO_CLOCKID(fd), &ts), EINVAL);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(step_realtime)
+{
+	struct timespec realtime, monotonic, new_realtime, new_monotonic;
+	struct timeval tv;
+
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME, &realtime));
+	TEST_SUCC(clock_gettime(CLOCK_MONOTONIC, &monotonic));
+
+	realtime.tv_sec += STEP_SECS;
+	TEST_SUCC(clock_settime(CLOCK_REALTIME, &realtime));
+
+	// The real time is stepped, but the monotonic time is not.
+	TEST_RES(clock_gettime(CLOCK_REALTIME, &new_realtime),
+		 new_realtime.tv_sec >= realtime.tv_sec &&
+			 new_realtime.tv_sec < realtime.tv_sec + 10);
+	TEST_RES(clock_gettime(CLOCK_MONOTONIC, &new_monotonic),
+		 new_monotonic.tv_sec >= monotonic.tv_sec &&
+			 new_monotonic.tv_sec < monotonic.tv_sec + 10);
+	TEST_RES(gettimeofday(&tv, NULL),
+		 tv.tv_sec >= realtime.tv_sec &&
+			 tv.tv_sec < realtime.tv_sec + 10);
+
+	tv.tv_sec -= STEP_SECS;
+	TEST_SUCC(settimeofday(&tv, NULL));
+	TEST_RES(clock_gettime(CLOCK_REALTIME, &new_realtime),
+		 new_realtime.tv_sec >= realtime.tv_sec - STEP_SECS &&
+			 new_realtime.tv_sec < realtime.tv_sec - STEP_SECS + 10);
+
+	// A null time only checks the permission.
+	TEST_SUCC(settimeofday(NULL, NULL));
+}
+END_TEST()
+
+static long to_nsecs(const struct timespec *ts)
+{
+	return ts->tv_sec * 1000000000L + ts->tv_nsec;
+}
+
+// Reads the real time repeatedly for `usecs`, and returns how much it falls
+// behind the monotonic time, or -1 if it ever jumps backwards.
+static long measure_lag(long usecs)
+{
+	struct timespec start_real, start_mono, real, mono;
+	long last_real;
+
+	clock_gettime(CLOCK_MONOTONIC, &start_mono);
+	clock_gettime(CLOCK_REALTIME, &start_real);
+	last_real = to_nsecs(&start_real);
+
+	do {
+		clock_gettime(CLOCK_REALTIME, &real);
+		if (to_nsecs(&real) < last_real)
+			return -1;
+		last_real = to_nsecs(&real);
+		clock_gettime(CLOCK_MONOTONIC, &mono);
+	} while (to_nsecs(&mono) - to_nsecs(&start_mono) < usecs * 1000);
+
+	return (to_nsecs(&mono) - to_nsecs(&start_mono)) -
+	       (to_nsecs(&real) - to_nsecs(&start_real));
+}
+
+FN_TEST(slew_realtime)
+{
+	struct timex tx = { .modes = ADJ_ADJTIME };
+
+	TEST_ERRNO(adjtimex(&tx), EINVAL);
+
+	tx.modes = ADJ_OFFSET_SINGLESHOT;
+	tx.offset = -SLEW_USECS;
+	TEST_SUCC(adjtimex(&tx));
+
+	// The real time never jumps backwards, but runs slower than the
+	// monotonic time until it is adjusted.
+	TEST_RES(measure_lag(SLEW_MEASURE_USECS), _ret >= SLEW_MIN_LAG_NSECS);
+
+	tx.modes = ADJ_OFFSET_SS_READ;
+	TEST_RES(adjtimex(&tx), tx.offset > -SLEW_USECS && tx.offset < 0);
+
+	// A zero offset cancels the remaining adjustment.
+	tx.modes = ADJ_OFFSET_SINGLESHOT;
+	tx.offset = 0;
+	TEST_RES(adjtimex(&tx), tx.offset > -SLEW_USECS && tx.offset < 0);
+	tx.modes = ADJ_OFFSET_SS_READ;
+	TEST_RES(adjtimex(&tx), tx.offset == 0);
+}
+END_TEST()
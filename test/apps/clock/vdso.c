@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <sched.h>
+#include <string.h>
+#include <time.h>
+#include <unistd.h>
+#include <sys/auxv.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+
+#include "../network/test.h"
+
+// The time functions of the C library use the vDSO if it exists, so the
+// results are compared with those of the system calls.
+
+static int syscall_clock_gettime(clockid_t clockid, struct timespec *ts)
+{
+	return syscall(SYS_clock_gettime, clockid, ts);
+}
+
+static int ts_le(const struct timespec *a, const struct timespec *b)
+{
+	return a->tv_sec < b->tv_sec ||
+	       (a->tv_sec == b->tv_sec && a->tv_nsec <= b->tv_nsec);
+}
+
+FN_TEST(auxv)
+{
+	const char *ehdr;
+
+	ehdr = (const char *)getauxval(AT_SYSINFO_EHDR);
+	TEST_RES(ehdr != NULL && memcmp(ehdr, "\177ELF", 4) == 0, _ret);
+}
+END_TEST()
+
+static int is_clock_consistent(clockid_t clockid)
+{
+	struct timespec start, now, end;
+	int i;
+
+	for (i = 0; i < 1000; ++i) {
+		if (syscall_clock_gettime(clockid, &start) < 0 ||
+		    clock_gettime(clockid, &now) < 0 ||
+		    syscall_clock_gettime(clockid, &end) < 0)
+			return 0;
+		if (!ts_le(&start, &now) || !ts_le(&now, &end))
+			return 0;
+	}
+
+	return 1;
+}
+
+FN_TEST(clock_gettime)
+{
+	TEST_RES(is_clock_consistent(CLOCK_REALTIME), _ret);
+	TEST_RES(is_clock_consistent(CLOCK_MONOTONIC), _ret);
+	TEST_RES(is_clock_consistent(CLOCK_BOOTTIME), _ret);
+}
+END_TEST()
+
+FN_TEST(gettimeofday)
+{
+	struct timespec start, end;
+	struct timeval now;
+
+	TEST_SUCC(syscall_clock_gettime(CLOCK_REALTIME, &start));
+	TEST_SUCC(gettimeofday(&now, NULL));
+	TEST_SUCC(syscall_clock_gettime(CLOCK_REALTIME, &end));
+	TEST_RES(now.tv_sec >= start.tv_sec && now.tv_sec <= end.tv_sec, _ret);
+
+	TEST_RES(time(NULL), _ret >= start.tv_sec);
+}
+END_TEST()
+
+FN_TEST(clock_getres)
+{
+	struct timespec res;
+
+	TEST_RES(clock_getres(CLOCK_MONOTONIC, &res),
+		 res.tv_sec == 0 && res.tv_nsec == 1);
+	TEST_RES(syscall(SYS_clock_getres, CLOCK_MONOTONIC, &res),
+		 res.tv_sec == 0 && res.tv_nsec == 1);
+	TEST_RES(syscall(SYS_clock_getres, CLOCK_MONOTONIC_COARSE, &res),
+		 res.tv_sec == 0 && res.tv_nsec > 1);
+	TEST_SUCC(syscall(SYS_clock_getres, CLOCK_PROCESS_CPUTIME_ID, NULL));
+	TEST_ERRNO(syscall(SYS_clock_getres, 100, &res), EINVAL);
+}
+END_TEST()
+
+FN_TEST(getcpu)
+{
+	unsigned int cpu, node, syscall_cpu, syscall_node;
+	cpu_set_t old_set, set;
+	int i;
+
+	TEST_SUCC(sched_getaffinity(0, sizeof(old_set), &old_set));
+
+	for (i = 0; i < CPU_SETSIZE; ++i) {
+		if (!CPU_ISSET(i, &old_set))
+			continue;
+
+		CPU_ZERO(&set);
+		CPU_SET(i, &set);
+		TEST_SUCC(sched_setaffinity(0, sizeof(set), &set));
+
+		TEST_RES(getcpu(&cpu, &node), cpu == i);
+		TEST_RES(syscall(SYS_getcpu, &syscall_cpu, &syscall_node, NULL),
+			 syscall_cpu == cpu && syscall_node == node);
+		TEST_RES(sched_getcpu(), _ret == i);
+	}
+
+	TEST_SUCC(sched_setaffinity(0, sizeof(old_set), &old_set));
+}
+END_TEST()
@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define SRC_NAME "/tmp/copy_file_range_src"
+#define DST_NAME "/tmp/copy_file_range_dst"
+#define EXT2_NAME "/ext2/copy_file_range_dst"
+
+#define DATA_SIZE 10000
+
+static int src_fd, dst_fd;
+static char data[DATA_SIZE];
+
+FN_SETUP(files)
+{
+	for (int i = 0; i < DATA_SIZE; i++)
+		data[i] = 'a' + i % 26;
+
+	src_fd = CHECK(open(SRC_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	CHECK_WITH(write(src_fd, data, DATA_SIZE), _ret == DATA_SIZE);
+
+	dst_fd = CHECK(open(DST_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+}
+END_SETUP()
+
+FN_TEST(copy_with_offsets)
+{
+	loff_t off_in = 100, off_out = 0;
+	static char buf[DATA_SIZE];
+
+	TEST_RES(copy_file_range(src_fd, &off_in, dst_fd, &off_out, 5000, 0),
+		 _ret == 5000 && off_in == 5100 && off_out == 5000);
+	TEST_RES(pread(dst_fd, buf, sizeof(buf), 0),
+		 _ret == 5000 && memcmp(buf, data + 100, 5000) == 0);
+
+	// The copy stops at the end of the source file.
+	off_in = DATA_SIZE - 10;
+	TEST_RES(copy_file_range(src_fd, &off_in, dst_fd, &off_out, 100, 0),
+		 _ret == 10 && off_in == DATA_SIZE);
+	TEST_RES(copy_file_range(src_fd, &off_in, dst_fd, &off_out, 100, 0),
+		 _ret == 0);
+
+	// The file offsets are not changed.
+	TEST_RES(lseek(src_fd, 0, SEEK_CUR), _ret == DATA_SIZE);
+	TEST_RES(lseek(dst_fd, 0, SEEK_CUR), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(copy_with_file_offsets)
+{
+	char buf[16];
+
+	TEST_RES(lseek(src_fd, 26, SEEK_SET), _ret == 26);
+	TEST_RES(lseek(dst_fd, 0, SEEK_SET), _ret == 0);
+
+	TEST_RES(copy_file_range(src_fd, NULL, dst_fd, NULL, 3, 0), _ret == 3);
+	TEST_RES(lseek(src_fd, 0, SEEK_CUR), _ret == 29);
+	TEST_RES(lseek(dst_fd, 0, SEEK_CUR), _ret == 3);
+	TEST_RES(pread(dst_fd, buf, 3, 0),
+		 _ret == 3 && memcmp(buf, "abc", 3) == 0);
+}
+END_TEST()
+
+FN_TEST(copy_errors)
+{
+	loff_t off_in = 0, off_out = 10;
+	int rdonly_fd, append_fd, dir_fd, ext2_fd;
+
+	TEST_ERRNO(copy_file_range(src_fd, NULL, dst_fd, NULL, 1, 1), EINVAL);
+
+	// The ranges overlap in the same file.
+	TEST_ERRNO(copy_file_range(src_fd, &off_in, src_fd, &off_out, 20, 0),
+		   EINVAL);
+	off_in = -1;
+	TEST_ERRNO(copy_file_range(src_fd, &off_in, dst_fd, NULL, 1, 0),
+		   EINVAL);
+
+	rdonly_fd = TEST_SUCC(open(DST_NAME, O_RDONLY));
+	TEST_ERRNO(copy_file_range(src_fd, NULL, rdonly_fd, NULL, 1, 0),
+		   EBADF);
+	TEST_SUCC(close(rdonly_fd));
+
+	append_fd = TEST_SUCC(open(DST_NAME, O_WRONLY | O_APPEND));
+	TEST_ERRNO(copy_file_range(src_fd, NULL, append_fd, NULL, 1, 0),
+		   EBADF);
+	TEST_SUCC(close(append_fd));
+
+	dir_fd = TEST_SUCC(open("/tmp", O_RDONLY | O_DIRECTORY));
+	TEST_ERRNO(copy_file_range(dir_fd, NULL, dst_fd, NULL, 1, 0), EISDIR);
+	TEST_SUCC(close(dir_fd));
+
+	ext2_fd = TEST_SUCC(open(EXT2_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	TEST_ERRNO(copy_file_range(src_fd, NULL, ext2_fd, NULL, 1, 0), EXDEV);
+	TEST_SUCC(close(ext2_fd));
+	TEST_SUCC(unlink(EXT2_NAME));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(src_fd));
+	CHECK(close(dst_fd));
+	CHECK(unlink(SRC_NAME));
+	CHECK(unlink(DST_NAME));
+}
+END_SETUP()
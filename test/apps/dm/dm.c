@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <linux/dm-ioctl.h>
+#include <linux/loop.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define IMAGE_FILE_0 "/ext2/dm_test0.img"
+#define IMAGE_FILE_1 "/ext2/dm_test1.img"
+#define IMAGE_SIZE (64 * 1024)
+#define IMAGE_SECTORS (IMAGE_SIZE / 512)
+
+#define DEV_NAME "dm_test"
+#define DEV_NAME_2 "dm_test2"
+#define DM_MAJOR 253
+
+#define ROOT_DIGEST \
+	"0000000000000000000000000000000000000000000000000000000000000000"
+
+static int ctl_fd;
+static int loop_fds[2];
+static int loop_indexes[2];
+static char table_params[2][32];
+static char dev_path[32];
+
+static union {
+	struct dm_ioctl io;
+	char buf[16384];
+} dm;
+static char *next_target;
+
+static void init_io(const char *name, __u32 flags)
+{
+	memset(&dm, 0, sizeof(dm));
+	dm.io.version[0] = DM_VERSION_MAJOR;
+	dm.io.data_size = sizeof(dm);
+	dm.io.data_start = sizeof(struct dm_ioctl);
+	dm.io.flags = flags;
+	if (name != NULL)
+		strcpy(dm.io.name, name);
+
+	next_target = dm.buf + sizeof(struct dm_ioctl);
+}
+
+static void add_target(__u64 start, __u64 len, const char *type,
+		       const char *params)
+{
+	struct dm_target_spec *spec = (struct dm_target_spec *)next_target;
+	size_t size;
+
+	spec->sector_start = start;
+	spec->length = len;
+	strcpy(spec->target_type, type);
+	strcpy((char *)(spec + 1), params);
+
+	size = sizeof(*spec) + strlen(params) + 1;
+	size = (size + 7) & ~7;
+	spec->next = size;
+
+	next_target += size;
+	++dm.io.target_count;
+}
+
+static int dm_ioctl(unsigned long cmd)
+{
+	return ioctl(ctl_fd, cmd, &dm);
+}
+
+static int setup_loop(const char *image_file, int *loop_index)
+{
+	struct loop_config config;
+	char path[32];
+	int loop_ctl_fd, file_fd, loop_fd;
+
+	file_fd = CHECK(open(image_file, O_CREAT | O_TRUNC | O_RDWR, 0644));
+	CHECK(ftruncate(file_fd, IMAGE_SIZE));
+
+	loop_ctl_fd = CHECK(open("/dev/loop-control", O_RDWR));
+	*loop_index = CHECK(ioctl(loop_ctl_fd, LOOP_CTL_GET_FREE));
+	CHECK(close(loop_ctl_fd));
+
+	snprintf(path, sizeof(path), "/dev/loop%d", *loop_index);
+	loop_fd = CHECK(open(path, O_RDWR));
+
+	memset(&config, 0, sizeof(config));
+	config.fd = file_fd;
+	CHECK(ioctl(loop_fd, LOOP_CONFIGURE, &config));
+	CHECK(close(file_fd));
+
+	return loop_fd;
+}
+
+FN_SETUP(loop_devices)
+{
+	loop_fds[0] = setup_loop(IMAGE_FILE_0, &loop_indexes[0]);
+	loop_fds[1] = setup_loop(IMAGE_FILE_1, &loop_indexes[1]);
+
+	snprintf(table_params[0], sizeof(table_params[0]), "7:%d 0",
+		 loop_indexes[0]);
+	snprintf(table_params[1], sizeof(table_params[1]), "7:%d 8",
+		 loop_indexes[1]);
+}
+END_SETUP()
+
+FN_SETUP(control)
+{
+	ctl_fd = CHECK(open("/dev/mapper/control", O_RDWR));
+}
+END_SETUP()
+
+FN_TEST(version)
+{
+	init_io(NULL, 0);
+	TEST_RES(dm_ioctl(DM_VERSION), dm.io.version[0] == DM_VERSION_MAJOR);
+
+	// The major version must match.
+	init_io(NULL, 0);
+	dm.io.version[0] = DM_VERSION_MAJOR + 1;
+	TEST_ERRNO(dm_ioctl(DM_VERSION), EINVAL);
+}
+END_TEST()
+
+FN_TEST(create)
+{
+	struct stat stat_buf;
+
+	init_io(DEV_NAME, 0);
+	TEST_RES(dm_ioctl(DM_DEV_CREATE),
+		 major(dm.io.dev) == DM_MAJOR &&
+			 !(dm.io.flags & DM_ACTIVE_PRESENT_FLAG) &&
+			 dm.io.target_count == 0);
+	snprintf(dev_path, sizeof(dev_path), "/dev/dm-%d", minor(dm.io.dev));
+
+	TEST_RES(stat(dev_path, &stat_buf),
+		 S_ISBLK(stat_buf.st_mode) &&
+			 major(stat_buf.st_rdev) == DM_MAJOR);
+
+	init_io(DEV_NAME, 0);
+	TEST_ERRNO(dm_ioctl(DM_DEV_CREATE), EBUSY);
+	init_io("", 0);
+	TEST_ERRNO(dm_ioctl(DM_DEV_CREATE), EINVAL);
+}
+END_TEST()
+
+FN_TEST(invalid_table)
+{
+	char verity_params[256];
+
+	// The targets must not have gaps.
+	init_io(DEV_NAME, 0);
+	add_target(1, IMAGE_SECTORS, "linear", table_params[0]);
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), EINVAL);
+
+	init_io(DEV_NAME, 0);
+	add_target(0, IMAGE_SECTORS, "unknown", table_params[0]);
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), EINVAL);
+
+	init_io(DEV_NAME, 0);
+	add_target(0, IMAGE_SECTORS, "linear", "7:12345 0");
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), ENXIO);
+
+	init_io(DEV_NAME, 0);
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), EINVAL);
+
+	// The verity targets must be read-only.
+	snprintf(verity_params, sizeof(verity_params),
+		 "1 7:%d 7:%d 4096 4096 16 0 sha256 " ROOT_DIGEST " -",
+		 loop_indexes[0], loop_indexes[1]);
+	init_io(DEV_NAME, 0);
+	add_target(0, IMAGE_SECTORS, "verity", verity_params);
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), EINVAL);
+
+	// The verity targets cannot be larger than the data.
+	init_io(DEV_NAME, DM_READONLY_FLAG);
+	add_target(0, IMAGE_SECTORS * 2, "verity", verity_params);
+	TEST_ERRNO(dm_ioctl(DM_TABLE_LOAD), EINVAL);
+
+	init_io(DEV_NAME, 0);
+	TEST_RES(dm_ioctl(DM_DEV_STATUS),
+		 !(dm.io.flags & DM_INACTIVE_PRESENT_FLAG));
+}
+END_TEST()
+
+FN_TEST(load_and_resume)
+{
+	init_io(DEV_NAME, 0);
+	add_target(0, IMAGE_SECTORS, "linear", table_params[0]);
+	add_target(IMAGE_SECTORS, IMAGE_SECTORS - 8, "linear",
+		   table_params[1]);
+	TEST_RES(dm_ioctl(DM_TABLE_LOAD),
+		 (dm.io.flags & DM_INACTIVE_PRESENT_FLAG) &&
+			 !(dm.io.flags & DM_ACTIVE_PRESENT_FLAG));
+
+	init_io(DEV_NAME, 0);
+	TEST_RES(dm_ioctl(DM_DEV_SUSPEND),
+		 !(dm.io.flags & DM_INACTIVE_PRESENT_FLAG) &&
+			 (dm.io.flags & DM_ACTIVE_PRESENT_FLAG) &&
+			 !(dm.io.flags & DM_SUSPEND_FLAG) &&
+			 dm.io.target_count == 2);
+
+	init_io(DEV_NAME, DM_SUSPEND_FLAG);
+	TEST_RES(dm_ioctl(DM_DEV_SUSPEND), dm.io.flags & DM_SUSPEND_FLAG);
+	init_io(DEV_NAME, 0);
+	TEST_RES(dm_ioctl(DM_DEV_STATUS), dm.io.flags & DM_SUSPEND_FLAG);
+	init_io(DEV_NAME, 0);
+	TEST_RES(dm_ioctl(DM_DEV_SUSPEND), !(dm.io.flags & DM_SUSPEND_FLAG));
+}
+END_TEST()
+
+FN_TEST(table_status)
+{
+	struct dm_target_spec *spec;
+	char *data;
+
+	init_io(DEV_NAME, DM_STATUS_TABLE_FLAG);
+	TEST_RES(dm_ioctl(DM_TABLE_STATUS),
+		 dm.io.target_count == 2 &&
+			 !(dm.io.flags & DM_BUFFER_FULL_FLAG));
+
+	data = dm.buf + dm.io.data_start;
+	spec = (struct dm_target_spec *)data;
+	TEST_RES(strcmp((char *)(spec + 1), table_params[0]),
+		 _ret == 0 && spec->sector_start == 0 &&
+			 spec->length == IMAGE_SECTORS &&
+			 strcmp(spec->target_type, "linear") == 0);
+
+	spec = (struct dm_target_spec *)(data + spec->next);
+	TEST_RES(strcmp((char *)(spec + 1), table_params[1]),
+		 _ret == 0 && spec->sector_start == IMAGE_SECTORS &&
+			 spec->length == IMAGE_SECTORS - 8 &&
+			 strcmp(spec->target_type, "linear") == 0);
+
+	// The buffer is too small for the targets.
+	init_io(DEV_NAME, DM_STATUS_TABLE_FLAG);
+	dm.io.data_size = sizeof(struct dm_ioctl) + 8;
+	TEST_RES(dm_ioctl(DM_TABLE_STATUS),
+		 dm.io.flags & DM_BUFFER_FULL_FLAG);
+}
+END_TEST()
+
+FN_TEST(stacked_device)
+{
+	char params[32];
+
+	init_io(DEV_NAME_2, 0);
+	TEST_SUCC(dm_ioctl(DM_DEV_CREATE));
+
+	snprintf(params, sizeof(params), "%s 0", dev_path);
+	init_io(DEV_NAME_2, 0);
+	add_target(0, IMAGE_SECTORS, "linear", params);
+	TEST_SUCC(dm_ioctl(DM_TABLE_LOAD));
+
+	// The device is used by the table of the other device.
+	init_io(DEV_NAME, 0);
+	TEST_ERRNO(dm_ioctl(DM_DEV_REMOVE), EBUSY);
+
+	init_io(DEV_NAME_2, 0);
+	TEST_RES(dm_ioctl(DM_TABLE_CLEAR),
+		 !(dm.io.flags & DM_INACTIVE_PRESENT_FLAG));
+	TEST_SUCC(dm_ioctl(DM_DEV_REMOVE));
+}
+END_TEST()
+
+FN_TEST(remove)
+{
+	init_io(DEV_NAME, 0);
+	TEST_SUCC(dm_ioctl(DM_DEV_REMOVE));
+	TEST_ERRNO(dm_ioctl(DM_DEV_STATUS), ENXIO);
+	TEST_ERRNO(access(dev_path, F_OK), ENOENT);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(ctl_fd));
+
+	CHECK(ioctl(loop_fds[0], LOOP_CLR_FD));
+	CHECK(ioctl(loop_fds[1], LOOP_CLR_FD));
+	CHECK(close(loop_fds[0]));
+	CHECK(close(loop_fds[1]));
+
+	CHECK(unlink(IMAGE_FILE_0));
+	CHECK(unlink(IMAGE_FILE_1));
+}
+END_SETUP()
@@ -1,8 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
 #include "../network/test.h"
+#include <time.h>
 #include <unistd.h>
 #include <sys/epoll.h>
+#include <sys/syscall.h>
+
+#ifndef __NR_epoll_pwait2
+#define __NR_epoll_pwait2 441
+#endif
 
 FN_TEST(epoll_add_del)
 {
@@ -167,3 +173,87 @@ FN_TEST(epoll_flags_oneshot)
 	TEST_SUCC(close(wfd));
 }
 END_TEST()
+
+FN_TEST(epoll_flags_exclusive)
+{
+	int fildes[2];
+	int epfd, epfd2, rfd, wfd;
+	struct epoll_event ev;
+
+	// Setup pipes
+	TEST_SUCC(pipe(fildes));
+	rfd = fildes[0];
+	wfd = fildes[1];
+
+	// Setup epoll
+	epfd = TEST_SUCC(epoll_create1(0));
+	epfd2 = TEST_SUCC(epoll_create1(0));
+
+	// Some events and flags cannot be used with EPOLLEXCLUSIVE
+	ev.events = EPOLLIN | EPOLLRDHUP | EPOLLEXCLUSIVE;
+	ev.data.fd = rfd;
+	TEST_ERRNO(epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &ev), EINVAL);
+	ev.events = EPOLLIN | EPOLLONESHOT | EPOLLEXCLUSIVE;
+	TEST_ERRNO(epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &ev), EINVAL);
+
+	// Epoll files cannot be added exclusively
+	ev.events = EPOLLIN | EPOLLEXCLUSIVE;
+	TEST_ERRNO(epoll_ctl(epfd, EPOLL_CTL_ADD, epfd2, &ev), EINVAL);
+
+	// Exclusive entries cannot be modified
+	TEST_SUCC(epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &ev));
+	TEST_ERRNO(epoll_ctl(epfd, EPOLL_CTL_MOD, rfd, &ev), EINVAL);
+	ev.events = EPOLLIN;
+	TEST_ERRNO(epoll_ctl(epfd, EPOLL_CTL_MOD, rfd, &ev), EINVAL);
+
+	// Events are still reported without waiters
+	TEST_SUCC(write(wfd, "", 1));
+	TEST_RES(epoll_wait(epfd, &ev, 1, 0),
+		 _ret == 1 && ev.data.fd == rfd && ev.events == EPOLLIN);
+
+	// Clean up
+	TEST_SUCC(close(epfd2));
+	TEST_SUCC(close(epfd));
+	TEST_SUCC(close(rfd));
+	TEST_SUCC(close(wfd));
+}
+END_TEST()
+
+FN_TEST(epoll_pwait2_timeout)
+{
+	int fildes[2];
+	int epfd, rfd, wfd;
+	struct epoll_event ev;
+	struct timespec timeout = { .tv_sec = 0, .tv_nsec = 1000000 };
+
+	// Setup pipes
+	TEST_SUCC(pipe(fildes));
+	rfd = fildes[0];
+	wfd = fildes[1];
+
+	// Setup epoll
+	epfd = TEST_SUCC(epoll_create1(0));
+	ev.events = EPOLLIN;
+	ev.data.fd = rfd;
+	TEST_SUCC(epoll_ctl(epfd, EPOLL_CTL_ADD, rfd, &ev));
+
+	// Time out without events
+	TEST_RES(syscall(__NR_epoll_pwait2, epfd, &ev, 1, &timeout, NULL, 0),
+		 _ret == 0);
+
+	// Invalid timeout
+	timeout.tv_nsec = -1;
+	TEST_ERRNO(syscall(__NR_epoll_pwait2, epfd, &ev, 1, &timeout, NULL, 0),
+		   EINVAL);
+
+	// Wait without timeout after writing something
+	TEST_SUCC(write(wfd, "", 1));
+	TEST_RES(syscall(__NR_epoll_pwait2, epfd, &ev, 1, NULL, NULL, 0),
+		 _ret == 1 && ev.data.fd == rfd);
+
+	// Clean up
+	TEST_SUCC(close(epfd));
+	TEST_SUCC(close(rfd));
+	TEST_SUCC(close(wfd));
+}
+END_TEST()
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <poll.h>
+#include <string.h>
+#include <time.h>
+#include <unistd.h>
+#include <linux/input.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define EVDEV_PATH "/dev/input/event0"
+#define INPUT_MAJOR 13
+
+#define NBITS(max) ((max) / (8 * sizeof(long)) + 1)
+#define TEST_BIT(bits, bit) \
+	(((bits)[(bit) / (8 * sizeof(long))] >> ((bit) & 63)) & 1)
+
+static int fd;
+static int fd2;
+
+FN_SETUP(open)
+{
+	fd = CHECK(open(EVDEV_PATH, O_RDONLY | O_NONBLOCK));
+	fd2 = CHECK(open(EVDEV_PATH, O_RDONLY | O_NONBLOCK));
+}
+END_SETUP()
+
+FN_TEST(device_node)
+{
+	struct stat stat_buf;
+
+	TEST_RES(stat(EVDEV_PATH, &stat_buf),
+		 S_ISCHR(stat_buf.st_mode) &&
+			 major(stat_buf.st_rdev) == INPUT_MAJOR &&
+			 minor(stat_buf.st_rdev) == 64);
+}
+END_TEST()
+
+FN_TEST(version_and_name)
+{
+	int version;
+	char name[256];
+
+	TEST_RES(ioctl(fd, EVIOCGVERSION, &version), version == EV_VERSION);
+
+	memset(name, 0xff, sizeof(name));
+	TEST_RES(ioctl(fd, EVIOCGNAME(sizeof(name)), name),
+		 _ret > 1 && _ret == strlen(name) + 1);
+}
+END_TEST()
+
+FN_TEST(capabilities)
+{
+	unsigned long ev_bits[1] = { 0 };
+	unsigned long key_bits[NBITS(KEY_MAX)];
+	unsigned long key_state[NBITS(KEY_MAX)];
+
+	TEST_RES(ioctl(fd, EVIOCGBIT(0, sizeof(ev_bits)), ev_bits),
+		 _ret == sizeof(ev_bits) && TEST_BIT(ev_bits, EV_SYN) &&
+			 TEST_BIT(ev_bits, EV_KEY) &&
+			 TEST_BIT(ev_bits, EV_REP));
+
+	memset(key_bits, 0, sizeof(key_bits));
+	TEST_RES(ioctl(fd, EVIOCGBIT(EV_KEY, sizeof(key_bits)), key_bits),
+		 _ret == sizeof(key_bits) && TEST_BIT(key_bits, KEY_A) &&
+			 TEST_BIT(key_bits, KEY_ENTER) &&
+			 !TEST_BIT(key_bits, BTN_LEFT));
+
+	memset(key_state, 0xff, sizeof(key_state));
+	TEST_RES(ioctl(fd, EVIOCGKEY(sizeof(key_state)), key_state),
+		 _ret == sizeof(key_state) && !TEST_BIT(key_state, KEY_A));
+}
+END_TEST()
+
+FN_TEST(repeat)
+{
+	unsigned int rep[2];
+	unsigned int new_rep[2] = { 500, 50 };
+
+	TEST_RES(ioctl(fd, EVIOCGREP, rep), rep[0] == 250 && rep[1] == 33);
+
+	TEST_SUCC(ioctl(fd, EVIOCSREP, new_rep));
+	TEST_RES(ioctl(fd2, EVIOCGREP, rep), rep[0] == 500 && rep[1] == 50);
+
+	new_rep[0] = 250;
+	new_rep[1] = 33;
+	TEST_SUCC(ioctl(fd, EVIOCSREP, new_rep));
+}
+END_TEST()
+
+FN_TEST(read_and_poll)
+{
+	struct input_event event;
+	struct pollfd pfd = { .fd = fd, .events = POLLIN };
+
+	TEST_ERRNO(read(fd, &event, sizeof(event)), EAGAIN);
+	TEST_ERRNO(read(fd, &event, sizeof(event) - 1), EINVAL);
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0 && pfd.revents == 0);
+
+	TEST_ERRNO(write(fd, &event, sizeof(event)), EINVAL);
+}
+END_TEST()
+
+FN_TEST(grab)
+{
+	TEST_ERRNO(ioctl(fd, EVIOCGRAB, 0), EINVAL);
+
+	TEST_SUCC(ioctl(fd, EVIOCGRAB, 1));
+	TEST_ERRNO(ioctl(fd2, EVIOCGRAB, 1), EBUSY);
+	TEST_ERRNO(ioctl(fd, EVIOCGRAB, 1), EBUSY);
+	TEST_ERRNO(ioctl(fd2, EVIOCGRAB, 0), EINVAL);
+	TEST_SUCC(ioctl(fd, EVIOCGRAB, 0));
+
+	TEST_SUCC(ioctl(fd2, EVIOCGRAB, 1));
+	TEST_SUCC(ioctl(fd2, EVIOCGRAB, 0));
+}
+END_TEST()
+
+FN_TEST(clock_id)
+{
+	int clock_id = CLOCK_MONOTONIC;
+
+	TEST_SUCC(ioctl(fd, EVIOCSCLOCKID, &clock_id));
+
+	clock_id = CLOCK_PROCESS_CPUTIME_ID;
+	TEST_ERRNO(ioctl(fd, EVIOCSCLOCKID, &clock_id), EINVAL);
+
+	clock_id = CLOCK_REALTIME;
+	TEST_SUCC(ioctl(fd, EVIOCSCLOCKID, &clock_id));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(fd));
+	CHECK(close(fd2));
+}
+END_SETUP()
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <dirent.h>
+#include <fcntl.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define BLOCK_SIZE 4096
+
+#define TEST_DIR "/ext2/ext2_test"
+#define LARGE_DIR TEST_DIR "/large_dir"
+#define SPARSE_FILE TEST_DIR "/sparse_file"
+
+// The entries of the large directory span multiple blocks.
+#define NR_FILES 600
+// Every even block of the sparse file before `NR_DATA_BLOCKS * 2` is written.
+#define NR_DATA_BLOCKS 10
+#define SPARSE_FILE_BLOCKS (NR_DATA_BLOCKS * 2 + 4)
+
+static char path_buf[64];
+
+static const char *file_path(int idx)
+{
+	snprintf(path_buf, sizeof(path_buf), LARGE_DIR "/file_%03d", idx);
+	return path_buf;
+}
+
+static int create_file(const char *path)
+{
+	int fd;
+
+	fd = open(path, O_CREAT | O_EXCL | O_WRONLY, 0644);
+	if (fd < 0)
+		return -1;
+	return close(fd);
+}
+
+// Counts the entries in the directory, or returns -1 on errors.
+static int count_entries(const char *path)
+{
+	char buf[1024];
+	int fd, count = 0;
+	long len, offset;
+	struct dirent64 *dirent;
+
+	fd = open(path, O_RDONLY | O_DIRECTORY);
+	if (fd < 0)
+		return -1;
+
+	while ((len = syscall(SYS_getdents64, fd, buf, sizeof(buf))) > 0) {
+		for (offset = 0; offset < len; offset += dirent->d_reclen) {
+			dirent = (struct dirent64 *)(buf + offset);
+			count++;
+		}
+	}
+
+	close(fd);
+	return len < 0 ? -1 : count;
+}
+
+FN_SETUP(test_dir)
+{
+	CHECK(mkdir(TEST_DIR, 0755));
+	CHECK(mkdir(LARGE_DIR, 0755));
+}
+END_SETUP()
+
+FN_TEST(large_dir)
+{
+	int i;
+	struct stat st;
+
+	for (i = 0; i < NR_FILES; i++)
+		TEST_SUCC(create_file(file_path(i)));
+
+	TEST_RES(stat(LARGE_DIR, &st), st.st_size > BLOCK_SIZE);
+	TEST_RES(count_entries(LARGE_DIR), _ret == NR_FILES + 2);
+
+	TEST_SUCC(access(file_path(0), F_OK));
+	TEST_SUCC(access(file_path(NR_FILES / 2), F_OK));
+	TEST_SUCC(access(file_path(NR_FILES - 1), F_OK));
+	TEST_ERRNO(access(file_path(NR_FILES), F_OK), ENOENT);
+
+	// Removes the odd files, which leaves gaps in every block.
+	for (i = 1; i < NR_FILES; i += 2)
+		TEST_SUCC(unlink(file_path(i)));
+
+	TEST_RES(count_entries(LARGE_DIR), _ret == NR_FILES / 2 + 2);
+	TEST_SUCC(access(file_path(NR_FILES - 2), F_OK));
+	TEST_ERRNO(access(file_path(NR_FILES - 1), F_OK), ENOENT);
+
+	for (i = 0; i < NR_FILES; i += 2)
+		TEST_SUCC(unlink(file_path(i)));
+
+	TEST_RES(count_entries(LARGE_DIR), _ret == 2);
+}
+END_TEST()
+
+FN_TEST(sparse_file)
+{
+	char buf[BLOCK_SIZE];
+	int fd, i, j, expected, is_ok;
+
+	fd = TEST_SUCC(open(SPARSE_FILE, O_CREAT | O_TRUNC | O_RDWR, 0644));
+
+	for (i = 0; i < NR_DATA_BLOCKS * 2; i += 2) {
+		memset(buf, i + 1, sizeof(buf));
+		TEST_RES(pwrite(fd, buf, sizeof(buf), (off_t)i * BLOCK_SIZE),
+			 _ret == sizeof(buf));
+	}
+	TEST_SUCC(ftruncate(fd, (off_t)SPARSE_FILE_BLOCKS * BLOCK_SIZE));
+	TEST_SUCC(fsync(fd));
+
+	for (i = 0; i < SPARSE_FILE_BLOCKS; i++) {
+		TEST_RES(pread(fd, buf, sizeof(buf), (off_t)i * BLOCK_SIZE),
+			 _ret == sizeof(buf));
+
+		expected = i % 2 == 0 && i < NR_DATA_BLOCKS * 2 ? i + 1 : 0;
+		is_ok = 1;
+		for (j = 0; j < BLOCK_SIZE; j++)
+			if (buf[j] != expected)
+				is_ok = 0;
+		TEST_RES(is_ok, _ret == 1);
+	}
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(unlink(SPARSE_FILE));
+	CHECK(rmdir(LARGE_DIR));
+	CHECK(rmdir(TEST_DIR));
+}
+END_SETUP()
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/stat.h>
+#include <linux/falloc.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define FILE_SIZE (PAGE_SIZE * 4)
+
+#define RAMFS_FILE "/tmp/fallocate_test_file"
+#define EXT2_FILE "/ext2/fallocate_test_file"
+
+static int ramfs_fd;
+static int ext2_fd;
+static char buf[FILE_SIZE];
+
+static int fill_file(int fd)
+{
+	memset(buf, 'a', sizeof(buf));
+	if (ftruncate(fd, 0) < 0 || pwrite(fd, buf, sizeof(buf), 0) < 0)
+		return -1;
+	return 0;
+}
+
+static off_t file_size(int fd)
+{
+	struct stat stat_buf;
+
+	if (fstat(fd, &stat_buf) < 0)
+		return -1;
+	return stat_buf.st_size;
+}
+
+// Checks that the bytes in `[start, end)` are zeros and the others are untouched.
+static int check_zeros(int fd, off_t start, off_t end)
+{
+	off_t i;
+
+	memset(buf, 0xff, sizeof(buf));
+	if (pread(fd, buf, sizeof(buf), 0) != sizeof(buf))
+		return -1;
+
+	for (i = 0; i < FILE_SIZE; ++i)
+		if (buf[i] != (i >= start && i < end ? 0 : 'a'))
+			return -1;
+	return 0;
+}
+
+FN_SETUP(files)
+{
+	ramfs_fd = CHECK(open(RAMFS_FILE, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	ext2_fd = CHECK(open(EXT2_FILE, O_RDWR | O_CREAT | O_TRUNC, 0644));
+}
+END_SETUP()
+
+FN_TEST(invalid_modes)
+{
+	TEST_ERRNO(fallocate(ramfs_fd, FALLOC_FL_PUNCH_HOLE, 0, PAGE_SIZE),
+		   EOPNOTSUPP);
+	TEST_ERRNO(fallocate(ramfs_fd,
+			     FALLOC_FL_PUNCH_HOLE | FALLOC_FL_ZERO_RANGE |
+				     FALLOC_FL_KEEP_SIZE,
+			     0, PAGE_SIZE),
+		   EOPNOTSUPP);
+	TEST_ERRNO(fallocate(ramfs_fd, 0, -1, PAGE_SIZE), EINVAL);
+	TEST_ERRNO(fallocate(ramfs_fd, 0, 0, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(allocate)
+{
+	TEST_SUCC(ftruncate(ramfs_fd, 0));
+	TEST_SUCC(fallocate(ramfs_fd, FALLOC_FL_KEEP_SIZE, 0, FILE_SIZE));
+	TEST_RES(file_size(ramfs_fd), _ret == 0);
+	TEST_SUCC(fallocate(ramfs_fd, 0, 0, FILE_SIZE));
+	TEST_RES(file_size(ramfs_fd), _ret == FILE_SIZE);
+
+	TEST_SUCC(ftruncate(ext2_fd, 0));
+	TEST_SUCC(fallocate(ext2_fd, 0, PAGE_SIZE, FILE_SIZE - PAGE_SIZE));
+	TEST_RES(file_size(ext2_fd), _ret == FILE_SIZE);
+}
+END_TEST()
+
+FN_TEST(punch_hole)
+{
+	int mode = FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE;
+
+	// The hole covers two whole pages and two partial pages.
+	TEST_SUCC(fill_file(ramfs_fd));
+	TEST_SUCC(fallocate(ramfs_fd, mode, 100, PAGE_SIZE * 3));
+	TEST_RES(file_size(ramfs_fd), _ret == FILE_SIZE);
+	TEST_RES(check_zeros(ramfs_fd, 100, 100 + PAGE_SIZE * 3), _ret == 0);
+
+	// The hole is within a page.
+	TEST_SUCC(fill_file(ramfs_fd));
+	TEST_SUCC(fallocate(ramfs_fd, mode, 10, 20));
+	TEST_RES(check_zeros(ramfs_fd, 10, 30), _ret == 0);
+
+	// The hole extends beyond the end of the file.
+	TEST_SUCC(fill_file(ext2_fd));
+	TEST_SUCC(fallocate(ext2_fd, mode, PAGE_SIZE, FILE_SIZE));
+	TEST_RES(file_size(ext2_fd), _ret == FILE_SIZE);
+	TEST_RES(check_zeros(ext2_fd, PAGE_SIZE, FILE_SIZE), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(zero_range)
+{
+	TEST_SUCC(fill_file(ext2_fd));
+	TEST_SUCC(fallocate(ext2_fd, FALLOC_FL_ZERO_RANGE | FALLOC_FL_KEEP_SIZE,
+			    200, FILE_SIZE));
+	TEST_RES(file_size(ext2_fd), _ret == FILE_SIZE);
+	TEST_RES(check_zeros(ext2_fd, 200, FILE_SIZE), _ret == 0);
+
+	TEST_SUCC(fill_file(ext2_fd));
+	TEST_SUCC(fallocate(ext2_fd, FALLOC_FL_ZERO_RANGE, 300, FILE_SIZE));
+	TEST_RES(file_size(ext2_fd), _ret == FILE_SIZE + 300);
+	TEST_RES(check_zeros(ext2_fd, 300, FILE_SIZE), _ret == 0);
+
+	// Like Linux, tmpfs does not support zeroing ranges.
+	TEST_ERRNO(fallocate(ramfs_fd, FALLOC_FL_ZERO_RANGE, 0, PAGE_SIZE),
+		   EOPNOTSUPP);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(ramfs_fd));
+	CHECK(close(ext2_fd));
+	CHECK(unlink(RAMFS_FILE));
+	CHECK(unlink(EXT2_FILE));
+}
+END_SETUP()
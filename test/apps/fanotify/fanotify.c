@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/fanotify.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define TEST_FILE "/tmp/fanotify_test_file"
+#define EXT2_FILE "/ext2/fanotify_test_file"
+#define EXT2_IGNORED_FILE "/ext2/fanotify_ignored_file"
+
+static int notif_fd;
+static int perm_fd;
+static struct fanotify_event_metadata event;
+
+static int open_and_close(const char *path, int flags)
+{
+	int fd;
+
+	fd = open(path, flags, 0644);
+	if (fd < 0)
+		return -1;
+	return close(fd);
+}
+
+static int is_same_file(int fd, const char *path)
+{
+	struct stat fd_stat, path_stat;
+
+	if (fstat(fd, &fd_stat) < 0 || stat(path, &path_stat) < 0)
+		return 0;
+	return fd_stat.st_dev == path_stat.st_dev &&
+	       fd_stat.st_ino == path_stat.st_ino;
+}
+
+FN_SETUP(init)
+{
+	notif_fd = CHECK(fanotify_init(FAN_CLASS_NOTIF | FAN_NONBLOCK, O_RDONLY));
+	perm_fd = CHECK(fanotify_init(FAN_CLASS_CONTENT, O_RDONLY));
+
+	CHECK(open_and_close(TEST_FILE, O_RDWR | O_CREAT | O_TRUNC));
+	CHECK(open_and_close(EXT2_FILE, O_RDWR | O_CREAT | O_TRUNC));
+	CHECK(open_and_close(EXT2_IGNORED_FILE, O_RDWR | O_CREAT | O_TRUNC));
+}
+END_SETUP()
+
+FN_TEST(invalid_args)
+{
+	TEST_ERRNO(fanotify_init(FAN_CLASS_CONTENT | FAN_CLASS_PRE_CONTENT,
+				 O_RDONLY),
+		   EINVAL);
+
+	TEST_ERRNO(fanotify_mark(notif_fd, FAN_MARK_ADD, FAN_OPEN_PERM,
+				 AT_FDCWD, TEST_FILE),
+		   EINVAL);
+	TEST_ERRNO(fanotify_mark(notif_fd, FAN_MARK_ADD | FAN_MARK_REMOVE,
+				 FAN_OPEN, AT_FDCWD, TEST_FILE),
+		   EINVAL);
+	TEST_ERRNO(fanotify_mark(notif_fd, FAN_MARK_ADD, 0, AT_FDCWD,
+				 TEST_FILE),
+		   EINVAL);
+	TEST_ERRNO(fanotify_mark(notif_fd, FAN_MARK_REMOVE, FAN_OPEN, AT_FDCWD,
+				 TEST_FILE),
+		   ENOENT);
+	TEST_ERRNO(fanotify_mark(notif_fd, FAN_MARK_ADD | FAN_MARK_ONLYDIR,
+				 FAN_OPEN, AT_FDCWD, TEST_FILE),
+		   ENOTDIR);
+
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event) - 1), EINVAL);
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event)), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(notification_events)
+{
+	int fd;
+
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_ADD,
+				FAN_OPEN | FAN_MODIFY | FAN_CLOSE_WRITE,
+				AT_FDCWD, TEST_FILE));
+
+	fd = TEST_SUCC(open(TEST_FILE, O_WRONLY));
+	TEST_RES(write(fd, "a", 1), _ret == 1);
+	TEST_SUCC(close(fd));
+
+	// The consecutive events of the same file are merged.
+	TEST_RES(read(notif_fd, &event, sizeof(event)),
+		 _ret == sizeof(event) &&
+			 event.vers == FANOTIFY_METADATA_VERSION &&
+			 event.mask == (FAN_OPEN | FAN_MODIFY | FAN_CLOSE_WRITE) &&
+			 event.pid == getpid());
+	TEST_RES(is_same_file(event.fd, TEST_FILE), _ret);
+
+	// The file in the event does not generate events.
+	TEST_SUCC(close(event.fd));
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event)), EAGAIN);
+
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_REMOVE,
+				FAN_OPEN | FAN_MODIFY | FAN_CLOSE_WRITE,
+				AT_FDCWD, TEST_FILE));
+	TEST_SUCC(open_and_close(TEST_FILE, O_WRONLY));
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event)), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(mount_marks)
+{
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_ADD | FAN_MARK_MOUNT,
+				FAN_OPEN, AT_FDCWD, "/ext2"));
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_ADD | FAN_MARK_IGNORED_MASK,
+				FAN_OPEN, AT_FDCWD, EXT2_IGNORED_FILE));
+
+	TEST_SUCC(open_and_close(EXT2_IGNORED_FILE, O_RDONLY));
+	TEST_SUCC(open_and_close(TEST_FILE, O_RDONLY));
+	TEST_SUCC(open_and_close(EXT2_FILE, O_RDONLY));
+
+	TEST_RES(read(notif_fd, &event, sizeof(event)),
+		 _ret == sizeof(event) && event.mask == FAN_OPEN);
+	TEST_RES(is_same_file(event.fd, EXT2_FILE), _ret);
+	TEST_SUCC(close(event.fd));
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event)), EAGAIN);
+
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_FLUSH | FAN_MARK_MOUNT, 0,
+				AT_FDCWD, NULL));
+	TEST_SUCC(fanotify_mark(notif_fd, FAN_MARK_FLUSH, 0, AT_FDCWD, NULL));
+	TEST_SUCC(open_and_close(EXT2_FILE, O_RDONLY));
+	TEST_ERRNO(read(notif_fd, &event, sizeof(event)), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(permission_events)
+{
+	struct fanotify_response response;
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(fanotify_mark(perm_fd, FAN_MARK_ADD, FAN_OPEN_PERM, AT_FDCWD,
+				TEST_FILE));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (open(TEST_FILE, O_RDONLY) >= 0 || errno != EPERM)
+			_exit(1);
+		if (open(TEST_FILE, O_RDONLY) < 0)
+			_exit(2);
+		_exit(0);
+	}
+
+	TEST_RES(read(perm_fd, &event, sizeof(event)),
+		 _ret == sizeof(event) && event.mask == FAN_OPEN_PERM &&
+			 event.pid == pid);
+	response.fd = event.fd;
+	response.response = FAN_DENY;
+	TEST_RES(write(perm_fd, &response, sizeof(response)),
+		 _ret == sizeof(response));
+	TEST_ERRNO(write(perm_fd, &response, sizeof(response)), ENOENT);
+	TEST_SUCC(close(event.fd));
+
+	TEST_RES(read(perm_fd, &event, sizeof(event)),
+		 _ret == sizeof(event) && event.mask == FAN_OPEN_PERM &&
+			 event.pid == pid);
+	response.fd = event.fd;
+	response.response = FAN_ALLOW;
+	TEST_RES(write(perm_fd, &response, sizeof(response)),
+		 _ret == sizeof(response));
+	TEST_SUCC(close(event.fd));
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	response.response = 0;
+	TEST_ERRNO(write(perm_fd, &response, sizeof(response)), EINVAL);
+
+	TEST_SUCC(fanotify_mark(perm_fd, FAN_MARK_REMOVE, FAN_OPEN_PERM,
+				AT_FDCWD, TEST_FILE));
+}
+END_TEST()
+
+FN_TEST(close_allows_pending_events)
+{
+	int fanotify_fd;
+	int status;
+	pid_t pid;
+
+	fanotify_fd = TEST_SUCC(fanotify_init(FAN_CLASS_CONTENT, O_RDONLY));
+	TEST_SUCC(fanotify_mark(fanotify_fd, FAN_MARK_ADD, FAN_OPEN_PERM,
+				AT_FDCWD, TEST_FILE));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		close(fanotify_fd);
+		_exit(open(TEST_FILE, O_RDONLY) < 0);
+	}
+
+	TEST_RES(read(fanotify_fd, &event, sizeof(event)),
+		 _ret == sizeof(event) && event.mask == FAN_OPEN_PERM);
+	TEST_SUCC(close(event.fd));
+	TEST_SUCC(close(fanotify_fd));
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(notif_fd));
+	CHECK(close(perm_fd));
+
+	CHECK(unlink(TEST_FILE));
+	CHECK(unlink(EXT2_FILE));
+	CHECK(unlink(EXT2_IGNORED_FILE));
+}
+END_SETUP()
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <sched.h>
+#include <unistd.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#ifndef CLOSE_RANGE_UNSHARE
+#define CLOSE_RANGE_UNSHARE (1U << 1)
+#endif
+#ifndef CLOSE_RANGE_CLOEXEC
+#define CLOSE_RANGE_CLOEXEC (1U << 2)
+#endif
+
+#define NR_FDS 8
+
+static int fds[NR_FDS];
+
+static int do_close_range(unsigned int first, unsigned int last,
+			  unsigned int flags)
+{
+	return syscall(SYS_close_range, first, last, flags);
+}
+
+static int open_fds(void)
+{
+	int i;
+
+	for (i = 0; i < NR_FDS; i++) {
+		fds[i] = open("/dev/null", O_RDONLY);
+		if (fds[i] < 0)
+			return -1;
+		// The file descriptors should be allocated consecutively.
+		if (i > 0 && fds[i] != fds[i - 1] + 1)
+			return -1;
+	}
+
+	return 0;
+}
+
+FN_TEST(invalid_args)
+{
+	TEST_ERRNO(do_close_range(10, 9, 0), EINVAL);
+	TEST_ERRNO(do_close_range(0, ~0U, 1U << 0), EINVAL);
+	TEST_ERRNO(do_close_range(0, ~0U, 1U << 3), EINVAL);
+}
+END_TEST()
+
+FN_TEST(close_fds)
+{
+	TEST_SUCC(open_fds());
+
+	TEST_SUCC(do_close_range(fds[2], fds[5], 0));
+	TEST_ERRNO(fcntl(fds[2], F_GETFD), EBADF);
+	TEST_ERRNO(fcntl(fds[5], F_GETFD), EBADF);
+	TEST_SUCC(fcntl(fds[1], F_GETFD));
+	TEST_SUCC(fcntl(fds[6], F_GETFD));
+
+	// The lowest free file descriptors are reused first.
+	TEST_RES(open("/dev/null", O_RDONLY), _ret == fds[2]);
+	TEST_RES(dup2(fds[0], fds[4]), _ret == fds[4]);
+	TEST_RES(fcntl(fds[0], F_DUPFD, fds[3]), _ret == fds[3]);
+	TEST_RES(fcntl(fds[0], F_DUPFD, fds[3]), _ret == fds[5]);
+
+	// Closing a huge range is fine.
+	TEST_SUCC(do_close_range(fds[0], ~0U, 0));
+	TEST_ERRNO(fcntl(fds[0], F_GETFD), EBADF);
+	TEST_ERRNO(fcntl(fds[7], F_GETFD), EBADF);
+	TEST_SUCC(do_close_range(fds[0], ~0U, 0));
+}
+END_TEST()
+
+FN_TEST(set_cloexec)
+{
+	TEST_SUCC(open_fds());
+
+	TEST_SUCC(do_close_range(fds[1], fds[6], CLOSE_RANGE_CLOEXEC));
+	TEST_RES(fcntl(fds[0], F_GETFD), _ret == 0);
+	TEST_RES(fcntl(fds[1], F_GETFD), _ret == FD_CLOEXEC);
+	TEST_RES(fcntl(fds[6], F_GETFD), _ret == FD_CLOEXEC);
+	TEST_RES(fcntl(fds[7], F_GETFD), _ret == 0);
+
+	TEST_SUCC(do_close_range(fds[0], ~0U, 0));
+}
+END_TEST()
+
+static char child_stack[64 * 1024];
+
+static int close_in_child(void *arg)
+{
+	(void)arg;
+
+	if (do_close_range(fds[0], ~0U, CLOSE_RANGE_UNSHARE) < 0)
+		return 1;
+	if (fcntl(fds[0], F_GETFD) >= 0 || errno != EBADF)
+		return 2;
+	return 0;
+}
+
+FN_TEST(unshare)
+{
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(open_fds());
+
+	// The child shares the file table with the parent until it unshares the table.
+	pid = TEST_SUCC(clone(close_in_child, child_stack + sizeof(child_stack),
+			      CLONE_FILES | SIGCHLD, NULL));
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+	TEST_SUCC(fcntl(fds[0], F_GETFD));
+	TEST_SUCC(fcntl(fds[7], F_GETFD));
+
+	TEST_SUCC(do_close_range(fds[0], ~0U, CLOSE_RANGE_UNSHARE));
+	TEST_ERRNO(fcntl(fds[0], F_GETFD), EBADF);
+}
+END_TEST()
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define FILE_SIZE (4 * PAGE_SIZE)
+
+#define RAMFS_FILE "/tmp/seek_hole_test"
+#define EXT2_FILE "/ext2/seek_hole_test"
+
+static int fd;
+
+FN_SETUP(sparse_file)
+{
+	fd = CHECK(open(RAMFS_FILE, O_CREAT | O_TRUNC | O_RDWR, 0644));
+	CHECK(ftruncate(fd, FILE_SIZE));
+}
+END_SETUP()
+
+FN_TEST(all_holes)
+{
+	TEST_ERRNO(lseek(fd, 0, SEEK_DATA), ENXIO);
+	TEST_RES(lseek(fd, 0, SEEK_HOLE), _ret == 0);
+	TEST_RES(lseek(fd, PAGE_SIZE + 1, SEEK_HOLE), _ret == PAGE_SIZE + 1);
+}
+END_TEST()
+
+FN_TEST(data_and_holes)
+{
+	// Page 1 and page 2 contain data, while page 0 and page 3 are holes.
+	TEST_RES(pwrite(fd, "a", 1, PAGE_SIZE), _ret == 1);
+	TEST_RES(pwrite(fd, "b", 1, 3 * PAGE_SIZE - 1), _ret == 1);
+
+	TEST_RES(lseek(fd, 0, SEEK_DATA), _ret == PAGE_SIZE);
+	TEST_RES(lseek(fd, 0, SEEK_CUR), _ret == PAGE_SIZE);
+	TEST_RES(lseek(fd, PAGE_SIZE + 10, SEEK_DATA), _ret == PAGE_SIZE + 10);
+	TEST_ERRNO(lseek(fd, 3 * PAGE_SIZE, SEEK_DATA), ENXIO);
+
+	TEST_RES(lseek(fd, 0, SEEK_HOLE), _ret == 0);
+	TEST_RES(lseek(fd, PAGE_SIZE, SEEK_HOLE), _ret == 3 * PAGE_SIZE);
+	TEST_RES(lseek(fd, 3 * PAGE_SIZE + 10, SEEK_HOLE),
+		 _ret == 3 * PAGE_SIZE + 10);
+}
+END_TEST()
+
+FN_TEST(punch_hole)
+{
+	TEST_SUCC(fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE,
+			    PAGE_SIZE, PAGE_SIZE));
+
+	TEST_RES(lseek(fd, 0, SEEK_DATA), _ret == 2 * PAGE_SIZE);
+	TEST_RES(lseek(fd, PAGE_SIZE, SEEK_HOLE), _ret == PAGE_SIZE);
+}
+END_TEST()
+
+FN_TEST(out_of_range)
+{
+	TEST_ERRNO(lseek(fd, FILE_SIZE, SEEK_DATA), ENXIO);
+	TEST_ERRNO(lseek(fd, FILE_SIZE, SEEK_HOLE), ENXIO);
+	TEST_ERRNO(lseek(fd, -1, SEEK_DATA), ENXIO);
+	TEST_ERRNO(lseek(fd, -1, SEEK_HOLE), ENXIO);
+}
+END_TEST()
+
+FN_TEST(no_hole_support)
+{
+	int ext2_fd;
+	char buf[PAGE_SIZE] = {};
+
+	// Ext2 does not create holes, so the whole file is data.
+	ext2_fd = TEST_SUCC(
+		open(EXT2_FILE, O_CREAT | O_TRUNC | O_RDWR, 0644));
+	TEST_RES(pwrite(ext2_fd, buf, sizeof(buf), 2 * PAGE_SIZE),
+		 _ret == sizeof(buf));
+
+	TEST_RES(lseek(ext2_fd, 10, SEEK_DATA), _ret == 10);
+	TEST_RES(lseek(ext2_fd, 10, SEEK_HOLE), _ret == 3 * PAGE_SIZE);
+
+	TEST_SUCC(close(ext2_fd));
+	TEST_SUCC(unlink(EXT2_FILE));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(fd));
+	CHECK(unlink(RAMFS_FILE));
+}
+END_SETUP()
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/file.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define FILE_NAME "/tmp/file_lock_test_file"
+
+static int fd1;
+static int fd2;
+
+static int set_lock(int fd, int cmd, short type, off_t start, off_t len)
+{
+	struct flock lock = {
+		.l_type = type,
+		.l_whence = SEEK_SET,
+		.l_start = start,
+		.l_len = len,
+	};
+
+	return fcntl(fd, cmd, &lock);
+}
+
+FN_SETUP(files)
+{
+	fd1 = CHECK(open(FILE_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	fd2 = CHECK(open(FILE_NAME, O_RDWR));
+}
+END_SETUP()
+
+FN_TEST(ofd_locks)
+{
+	struct flock lock = {
+		.l_type = F_WRLCK,
+		.l_whence = SEEK_SET,
+		.l_start = 0,
+		.l_len = 10,
+		.l_pid = 1,
+	};
+
+	TEST_ERRNO(fcntl(fd1, F_OFD_SETLK, &lock), EINVAL);
+
+	// OFD locks set via different open files conflict, even in the same process.
+	TEST_SUCC(set_lock(fd1, F_OFD_SETLK, F_WRLCK, 0, 10));
+	TEST_ERRNO(set_lock(fd2, F_OFD_SETLK, F_RDLCK, 5, 10), EAGAIN);
+
+	lock.l_type = F_RDLCK;
+	lock.l_pid = 0;
+	TEST_RES(fcntl(fd2, F_OFD_GETLK, &lock),
+		 lock.l_type == F_WRLCK && lock.l_start == 0 &&
+			 lock.l_len == 10 && lock.l_pid == -1);
+
+	// Traditional POSIX locks conflict with OFD locks.
+	TEST_ERRNO(set_lock(fd2, F_SETLK, F_WRLCK, 0, 1), EAGAIN);
+
+	TEST_SUCC(set_lock(fd1, F_OFD_SETLK, F_UNLCK, 0, 0));
+	TEST_SUCC(set_lock(fd2, F_OFD_SETLK, F_RDLCK, 5, 10));
+	TEST_SUCC(set_lock(fd2, F_OFD_SETLK, F_UNLCK, 0, 0));
+}
+END_TEST()
+
+FN_TEST(posix_locks_released_on_close)
+{
+	int fd3;
+	struct flock lock = {
+		.l_type = F_WRLCK,
+		.l_whence = SEEK_SET,
+		.l_start = 0,
+		.l_len = 0,
+	};
+
+	fd3 = TEST_SUCC(open(FILE_NAME, O_RDWR));
+	TEST_SUCC(set_lock(fd1, F_SETLK, F_WRLCK, 0, 0));
+	TEST_RES(fcntl(fd2, F_OFD_GETLK, &lock),
+		 lock.l_type == F_WRLCK && lock.l_pid == getpid());
+
+	// Closing any file descriptor of the file releases the locks of the process.
+	TEST_SUCC(close(fd3));
+	lock.l_type = F_WRLCK;
+	lock.l_pid = 0;
+	TEST_RES(fcntl(fd2, F_OFD_GETLK, &lock), lock.l_type == F_UNLCK);
+}
+END_TEST()
+
+FN_TEST(deadlock_and_exit)
+{
+	int pipe_fds[2];
+	pid_t pid;
+	char c;
+	int status;
+
+	TEST_SUCC(pipe(pipe_fds));
+	TEST_SUCC(set_lock(fd1, F_SETLK, F_WRLCK, 0, 1));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		CHECK(set_lock(fd1, F_SETLK, F_WRLCK, 1, 1));
+		CHECK(write(pipe_fds[1], "x", 1));
+		CHECK(set_lock(fd1, F_SETLKW, F_WRLCK, 0, 1));
+		_exit(0);
+	}
+
+	TEST_RES(read(pipe_fds[0], &c, 1), _ret == 1);
+	// Wait for the child to be blocked.
+	usleep(100 * 1000);
+
+	TEST_ERRNO(set_lock(fd1, F_SETLKW, F_WRLCK, 1, 1), EDEADLK);
+
+	// The locks of the child are released when it exits.
+	TEST_SUCC(set_lock(fd1, F_SETLK, F_UNLCK, 0, 1));
+	TEST_RES(wait(&status), _ret == pid && WIFEXITED(status) &&
+					WEXITSTATUS(status) == 0);
+	TEST_SUCC(set_lock(fd1, F_SETLK, F_WRLCK, 0, 2));
+	TEST_SUCC(set_lock(fd1, F_SETLK, F_UNLCK, 0, 0));
+
+	TEST_SUCC(close(pipe_fds[0]));
+	TEST_SUCC(close(pipe_fds[1]));
+}
+END_TEST()
+
+FN_TEST(flock_conversion)
+{
+	TEST_SUCC(flock(fd1, LOCK_SH));
+	TEST_SUCC(flock(fd2, LOCK_SH));
+
+	TEST_ERRNO(flock(fd1, LOCK_EX | LOCK_NB), EWOULDBLOCK);
+	// The failed conversion has released the shared lock.
+	TEST_SUCC(flock(fd2, LOCK_EX | LOCK_NB));
+
+	TEST_SUCC(flock(fd2, LOCK_UN));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(fd1));
+	CHECK(close(fd2));
+	CHECK(unlink(FILE_NAME));
+}
+END_SETUP()
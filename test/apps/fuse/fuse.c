@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <dirent.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <linux/fuse.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define MNT_DIR "/tmp/fuse_mnt"
+#define FILE_NAME "hello"
+#define FILE_PATH MNT_DIR "/" FILE_NAME
+#define FILE_ID 2
+
+#define FUSE_SUPER_MAGIC 0x65735546
+
+static const char content[] = "hello, fuse\n";
+
+static char request[FUSE_MIN_READ_BUFFER + 4096];
+
+static void fill_attr(struct fuse_attr *attr, uint64_t nodeid)
+{
+	memset(attr, 0, sizeof(*attr));
+	attr->ino = nodeid;
+	if (nodeid == FUSE_ROOT_ID) {
+		attr->mode = S_IFDIR | 0755;
+		attr->nlink = 2;
+	} else {
+		attr->mode = S_IFREG | 0644;
+		attr->nlink = 1;
+		attr->size = sizeof(content) - 1;
+	}
+	attr->blksize = 4096;
+}
+
+static void fill_entry(struct fuse_entry_out *entry, uint64_t nodeid)
+{
+	memset(entry, 0, sizeof(*entry));
+	entry->nodeid = nodeid;
+	fill_attr(&entry->attr, nodeid);
+}
+
+static void reply(int fd, uint64_t unique, int error, const void *payload,
+		  size_t len)
+{
+	char buf[sizeof(struct fuse_out_header) + 4096];
+	struct fuse_out_header *header = (struct fuse_out_header *)buf;
+
+	header->len = sizeof(*header) + len;
+	header->error = error;
+	header->unique = unique;
+	if (len > 0)
+		memcpy(buf + sizeof(*header), payload, len);
+
+	if (write(fd, buf, header->len) != header->len)
+		_exit(1);
+}
+
+static size_t fill_dirents(char *buf, uint64_t offset)
+{
+	static const char *names[] = { ".", "..", FILE_NAME };
+	static const uint64_t nodeids[] = { FUSE_ROOT_ID, FUSE_ROOT_ID,
+					    FILE_ID };
+	size_t len = 0;
+
+	for (uint64_t i = offset; i < 3; i++) {
+		struct fuse_direntplus *entry =
+			(struct fuse_direntplus *)(buf + len);
+		size_t namelen = strlen(names[i]);
+
+		fill_entry(&entry->entry_out, nodeids[i]);
+		entry->dirent.ino = nodeids[i];
+		entry->dirent.off = i + 1;
+		entry->dirent.namelen = namelen;
+		entry->dirent.type = i < 2 ? DT_DIR : DT_REG;
+		memcpy(entry->dirent.name, names[i], namelen);
+		len += FUSE_DIRENTPLUS_SIZE(entry);
+	}
+
+	return len;
+}
+
+// Serves a directory that contains a read-only file, with the raw FUSE protocol.
+static void serve(int fd)
+{
+	char out[4096];
+
+	for (;;) {
+		ssize_t len = read(fd, request, sizeof(request));
+		if (len < 0)
+			_exit(errno == ENODEV ? 0 : 1);
+
+		struct fuse_in_header *header = (struct fuse_in_header *)request;
+		void *arg = request + sizeof(*header);
+		uint64_t unique = header->unique;
+
+		switch (header->opcode) {
+		case FUSE_INIT: {
+			struct fuse_init_out *init_out = (void *)out;
+
+			memset(init_out, 0, sizeof(*init_out));
+			init_out->major = FUSE_KERNEL_VERSION;
+			init_out->minor = 31;
+			init_out->flags = FUSE_DO_READDIRPLUS;
+			init_out->max_write = 4096;
+			reply(fd, unique, 0, init_out, sizeof(*init_out));
+			break;
+		}
+		case FUSE_LOOKUP:
+			if (header->nodeid != FUSE_ROOT_ID ||
+			    strcmp(arg, FILE_NAME) != 0) {
+				reply(fd, unique, -ENOENT, NULL, 0);
+				break;
+			}
+			fill_entry((void *)out, FILE_ID);
+			reply(fd, unique, 0, out, sizeof(struct fuse_entry_out));
+			break;
+		case FUSE_GETATTR: {
+			struct fuse_attr_out *attr_out = (void *)out;
+
+			memset(attr_out, 0, sizeof(*attr_out));
+			fill_attr(&attr_out->attr, header->nodeid);
+			reply(fd, unique, 0, attr_out, sizeof(*attr_out));
+			break;
+		}
+		case FUSE_OPEN:
+		case FUSE_OPENDIR: {
+			struct fuse_open_out *open_out = (void *)out;
+
+			memset(open_out, 0, sizeof(*open_out));
+			open_out->fh = header->opcode;
+			reply(fd, unique, 0, open_out, sizeof(*open_out));
+			break;
+		}
+		case FUSE_READ: {
+			struct fuse_read_in *read_in = arg;
+			size_t start = read_in->offset;
+			size_t end = start + read_in->size;
+
+			if (read_in->fh != FUSE_OPEN) {
+				reply(fd, unique, -EBADF, NULL, 0);
+				break;
+			}
+			if (start > sizeof(content) - 1)
+				start = sizeof(content) - 1;
+			if (end > sizeof(content) - 1)
+				end = sizeof(content) - 1;
+			reply(fd, unique, 0, content + start, end - start);
+			break;
+		}
+		case FUSE_READDIRPLUS: {
+			struct fuse_read_in *read_in = arg;
+
+			reply(fd, unique, 0, out, fill_dirents(out, read_in->offset));
+			break;
+		}
+		case FUSE_STATFS: {
+			struct fuse_statfs_out *statfs_out = (void *)out;
+
+			memset(statfs_out, 0, sizeof(*statfs_out));
+			statfs_out->st.blocks = 100;
+			statfs_out->st.bfree = 50;
+			statfs_out->st.bavail = 50;
+			statfs_out->st.bsize = 4096;
+			statfs_out->st.namelen = 255;
+			reply(fd, unique, 0, statfs_out, sizeof(*statfs_out));
+			break;
+		}
+		case FUSE_RELEASE:
+		case FUSE_RELEASEDIR:
+			reply(fd, unique, 0, NULL, 0);
+			break;
+		case FUSE_FORGET:
+		case FUSE_BATCH_FORGET:
+		case FUSE_INTERRUPT:
+			break;
+		default:
+			reply(fd, unique, -ENOSYS, NULL, 0);
+			break;
+		}
+	}
+}
+
+static int fuse_fd;
+static pid_t daemon_pid;
+
+FN_SETUP(mount)
+{
+	char options[128];
+
+	CHECK_WITH(mkdir(MNT_DIR, 0755), _ret == 0 || errno == EEXIST);
+	fuse_fd = CHECK(open("/dev/fuse", O_RDWR));
+
+	daemon_pid = CHECK(fork());
+	if (daemon_pid == 0)
+		serve(fuse_fd);
+
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=%d,group_id=%d", fuse_fd,
+		 getuid(), getgid());
+	CHECK(mount("test", MNT_DIR, "fuse.test", 0, options));
+}
+END_SETUP()
+
+FN_TEST(stat_and_read)
+{
+	struct stat st;
+	struct statfs stfs;
+	char buf[64];
+	int fd;
+
+	TEST_RES(stat(MNT_DIR, &st), S_ISDIR(st.st_mode) && st.st_ino == 1);
+	TEST_RES(stat(FILE_PATH, &st),
+		 S_ISREG(st.st_mode) && st.st_ino == FILE_ID &&
+			 st.st_size == sizeof(content) - 1);
+	TEST_ERRNO(stat(MNT_DIR "/missing", &st), ENOENT);
+	TEST_RES(statfs(MNT_DIR, &stfs),
+		 stfs.f_type == FUSE_SUPER_MAGIC && stfs.f_blocks == 100 &&
+			 stfs.f_bfree == 50);
+
+	fd = TEST_SUCC(open(FILE_PATH, O_RDONLY));
+	TEST_RES(read(fd, buf, sizeof(buf)),
+		 _ret == sizeof(content) - 1 &&
+			 memcmp(buf, content, _ret) == 0);
+	TEST_RES(pread(fd, buf, sizeof(buf), 7),
+		 _ret == 5 && memcmp(buf, "fuse\n", 5) == 0);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(readdir)
+{
+	DIR *dir;
+	struct dirent *entry;
+	int found = 0;
+
+	TEST_RES((dir = opendir(MNT_DIR)) == NULL ? -1 : 0, dir != NULL);
+	while ((entry = readdir(dir)) != NULL) {
+		if (strcmp(entry->d_name, FILE_NAME) == 0)
+			found = entry->d_ino == FILE_ID &&
+				entry->d_type == DT_REG;
+	}
+	TEST_RES(closedir(dir), found);
+}
+END_TEST()
+
+FN_TEST(mount_errors)
+{
+	char options[128];
+	int fd;
+
+	// The connection is already mounted.
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=0,group_id=0", fuse_fd);
+	TEST_ERRNO(mount("test", MNT_DIR, "fuse", 0, options), EINVAL);
+
+	// The file is not `/dev/fuse`.
+	fd = TEST_SUCC(open("/dev/null", O_RDWR));
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=0,group_id=0", fd);
+	TEST_ERRNO(mount("test", MNT_DIR, "fuse", 0, options), EINVAL);
+	TEST_SUCC(close(fd));
+
+	// The buffer is too small for the requests.
+	fd = TEST_SUCC(open("/dev/fuse", O_RDWR));
+	TEST_ERRNO(read(fd, options, sizeof(options)), EINVAL);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_SETUP(umount)
+{
+	CHECK(umount(MNT_DIR));
+	CHECK(close(fuse_fd));
+	CHECK(kill(daemon_pid, SIGKILL));
+	CHECK(waitpid(daemon_pid, NULL, 0));
+	CHECK(rmdir(MNT_DIR));
+}
+END_SETUP()
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <linux/futex.h>
+#include <pthread.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <time.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#ifndef SYS_futex_waitv
+#define SYS_futex_waitv 449
+#endif
+
+#define SIZE_U8 0x00
+#define SIZE_U16 0x01
+#define SIZE_U32 0x02
+#define SIZE_U64 0x03
+#define PRIVATE 128
+
+struct waitv {
+	uint64_t val;
+	uint64_t uaddr;
+	uint32_t flags;
+	uint32_t reserved;
+};
+
+static uint8_t futex_u8;
+static uint16_t futex_u16;
+static uint32_t futex_u32;
+static uint64_t futex_u64;
+
+static struct waitv waiters[4];
+
+static int futex_waitv(struct waitv *waiters, unsigned int nr, int clockid,
+		       long timeout_ns)
+{
+	struct timespec ts;
+
+	if (timeout_ns < 0)
+		return syscall(SYS_futex_waitv, waiters, nr, 0, NULL, clockid);
+
+	clock_gettime(clockid, &ts);
+	ts.tv_nsec += timeout_ns;
+	ts.tv_sec += ts.tv_nsec / 1000000000;
+	ts.tv_nsec %= 1000000000;
+	return syscall(SYS_futex_waitv, waiters, nr, 0, &ts, clockid);
+}
+
+FN_SETUP(init_waiters)
+{
+	waiters[0] = (struct waitv){ .val = 0,
+				     .uaddr = (uintptr_t)&futex_u8,
+				     .flags = SIZE_U8 | PRIVATE };
+	waiters[1] = (struct waitv){ .val = 0,
+				     .uaddr = (uintptr_t)&futex_u16,
+				     .flags = SIZE_U16 | PRIVATE };
+	waiters[2] = (struct waitv){ .val = 0,
+				     .uaddr = (uintptr_t)&futex_u32,
+				     .flags = SIZE_U32 | PRIVATE };
+	waiters[3] = (struct waitv){ .val = 0x100000000,
+				     .uaddr = (uintptr_t)&futex_u64,
+				     .flags = SIZE_U64 | PRIVATE };
+	futex_u64 = 0x100000000;
+}
+END_SETUP()
+
+FN_TEST(invalid_args)
+{
+	struct waitv bad = waiters[2];
+
+	TEST_ERRNO(futex_waitv(waiters, 0, CLOCK_MONOTONIC, 0), EINVAL);
+	TEST_ERRNO(futex_waitv(waiters, 129, CLOCK_MONOTONIC, 0), EINVAL);
+	TEST_ERRNO(syscall(SYS_futex_waitv, waiters, 4, 1, NULL, 0), EINVAL);
+	TEST_ERRNO(futex_waitv(waiters, 4, CLOCK_BOOTTIME, 0), EINVAL);
+
+	bad.reserved = 1;
+	TEST_ERRNO(futex_waitv(&bad, 1, CLOCK_MONOTONIC, 0), EINVAL);
+
+	bad = waiters[2];
+	bad.uaddr += 1;
+	TEST_ERRNO(futex_waitv(&bad, 1, CLOCK_MONOTONIC, 0), EINVAL);
+
+	bad = waiters[1];
+	bad.val = 0x10000;
+	TEST_ERRNO(futex_waitv(&bad, 1, CLOCK_MONOTONIC, 0), EINVAL);
+
+	bad = waiters[2];
+	bad.flags |= 0x4;
+	TEST_ERRNO(futex_waitv(&bad, 1, CLOCK_MONOTONIC, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(value_mismatch)
+{
+	futex_u16 = 1;
+	TEST_ERRNO(futex_waitv(waiters, 4, CLOCK_MONOTONIC, -1), EAGAIN);
+	futex_u16 = 0;
+
+	// All the 64 bits are compared, not only the low 32 bits.
+	futex_u64 = 0;
+	TEST_ERRNO(futex_waitv(waiters, 4, CLOCK_MONOTONIC, -1), EAGAIN);
+	futex_u64 = 0x100000000;
+}
+END_TEST()
+
+FN_TEST(timeout)
+{
+	TEST_ERRNO(futex_waitv(waiters, 4, CLOCK_MONOTONIC, 10000000),
+		   ETIMEDOUT);
+	TEST_ERRNO(futex_waitv(waiters, 4, CLOCK_REALTIME, 10000000),
+		   ETIMEDOUT);
+}
+END_TEST()
+
+static void *wake_u64(void *arg)
+{
+	(void)arg;
+
+	usleep(100000);
+	syscall(SYS_futex, &futex_u64, FUTEX_WAKE_PRIVATE, 1, NULL, NULL, 0);
+	return NULL;
+}
+
+static void *wake_u8(void *arg)
+{
+	(void)arg;
+
+	usleep(100000);
+	syscall(SYS_futex, &futex_u8, FUTEX_WAKE_PRIVATE, 1, NULL, NULL, 0);
+	return NULL;
+}
+
+FN_TEST(wake)
+{
+	pthread_t thread;
+
+	TEST_SUCC(pthread_create(&thread, NULL, wake_u64, NULL));
+	TEST_RES(futex_waitv(waiters, 4, CLOCK_MONOTONIC, -1), _ret == 3);
+	TEST_SUCC(pthread_join(thread, NULL));
+
+	TEST_SUCC(pthread_create(&thread, NULL, wake_u8, NULL));
+	TEST_RES(futex_waitv(waiters, 4, CLOCK_MONOTONIC, -1), _ret == 0);
+	TEST_SUCC(pthread_join(thread, NULL));
+}
+END_TEST()
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/random.h>
+
+#include "../network/test.h"
+
+#ifndef GRND_INSECURE
+#define GRND_INSECURE 0x0004
+#endif
+
+static int is_zero(const char *buf, size_t len)
+{
+	for (size_t i = 0; i < len; i++)
+		if (buf[i] != 0)
+			return 0;
+	return 1;
+}
+
+FN_TEST(getrandom_flags)
+{
+	char buf[64];
+
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(getrandom(buf, sizeof(buf), 0),
+		 _ret == sizeof(buf) && !is_zero(buf, sizeof(buf)));
+	TEST_RES(getrandom(buf, sizeof(buf), GRND_NONBLOCK),
+		 _ret == sizeof(buf));
+	TEST_RES(getrandom(buf, sizeof(buf), GRND_RANDOM), _ret == sizeof(buf));
+	TEST_RES(getrandom(buf, sizeof(buf), GRND_INSECURE),
+		 _ret == sizeof(buf));
+	TEST_RES(getrandom(buf, 0, 0), _ret == 0);
+
+	TEST_ERRNO(getrandom(buf, sizeof(buf), GRND_INSECURE | GRND_RANDOM),
+		   EINVAL);
+	TEST_ERRNO(getrandom(buf, sizeof(buf), 0x8), EINVAL);
+	TEST_ERRNO(getrandom(NULL, sizeof(buf), 0), EFAULT);
+}
+END_TEST()
+
+FN_TEST(getrandom_differs)
+{
+	char buf1[32], buf2[32];
+
+	TEST_RES(getrandom(buf1, sizeof(buf1), 0), _ret == sizeof(buf1));
+	TEST_RES(getrandom(buf2, sizeof(buf2), 0),
+		 _ret == sizeof(buf2) && memcmp(buf1, buf2, sizeof(buf1)) != 0);
+}
+END_TEST()
+
+FN_TEST(dev_random)
+{
+	char buf[64];
+	int fd;
+
+	fd = TEST_SUCC(open("/dev/urandom", O_RDWR));
+	TEST_RES(read(fd, buf, sizeof(buf)), _ret == sizeof(buf));
+	TEST_RES(write(fd, buf, sizeof(buf)), _ret == sizeof(buf));
+	TEST_SUCC(close(fd));
+
+	fd = TEST_SUCC(open("/dev/random", O_RDONLY));
+	TEST_RES(read(fd, buf, sizeof(buf)), _ret == sizeof(buf));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <termios.h>
+#include <unistd.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define HVC_PATH "/dev/hvc0"
+#define CONSOLE_PATH "/dev/console"
+#define VPORT_PATH "/dev/vport0p0"
+
+static int hvc_fd;
+static int console_fd;
+static struct winsize old_winsize;
+
+FN_SETUP(open)
+{
+	hvc_fd = CHECK(open(HVC_PATH, O_RDWR | O_NOCTTY));
+	console_fd = CHECK(open(CONSOLE_PATH, O_RDWR | O_NOCTTY));
+	CHECK(ioctl(hvc_fd, TIOCGWINSZ, &old_winsize));
+}
+END_SETUP()
+
+FN_TEST(device_node)
+{
+	struct stat stat_buf;
+
+	TEST_RES(stat(HVC_PATH, &stat_buf), S_ISCHR(stat_buf.st_mode));
+	TEST_RES(isatty(hvc_fd), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(termios)
+{
+	struct termios termios;
+
+	TEST_SUCC(tcgetattr(hvc_fd, &termios));
+	TEST_SUCC(tcsetattr(hvc_fd, TCSANOW, &termios));
+}
+END_TEST()
+
+FN_TEST(window_size)
+{
+	struct winsize winsize = { .ws_row = 24, .ws_col = 80 };
+	struct winsize console_winsize;
+
+	// The first console port is the console of the kernel, so the window size is shared.
+	TEST_SUCC(ioctl(hvc_fd, TIOCSWINSZ, &winsize));
+	TEST_RES(ioctl(console_fd, TIOCGWINSZ, &console_winsize),
+		 console_winsize.ws_row == 24 && console_winsize.ws_col == 80);
+
+	TEST_SUCC(ioctl(hvc_fd, TIOCSWINSZ, &old_winsize));
+}
+END_TEST()
+
+FN_TEST(write)
+{
+	TEST_RES(write(hvc_fd, "", 0), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(console_vport)
+{
+	struct stat stat_buf;
+
+	TEST_RES(stat(VPORT_PATH, &stat_buf),
+		 S_ISCHR(stat_buf.st_mode) && minor(stat_buf.st_rdev) == 0);
+
+	// The port is used by the console, so it cannot be opened as a virtio port.
+	TEST_ERRNO(open(VPORT_PATH, O_RDWR), ENXIO);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(console_fd));
+	CHECK(close(hvc_fd));
+}
+END_SETUP()
@@ -0,0 +1,441 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <linux/io_uring.h>
+#include <stdint.h>
+#include <sys/eventfd.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+struct ring {
+	int fd;
+	unsigned int sq_entries;
+	unsigned int cq_entries;
+	void *ring_ptr;
+	size_t ring_size;
+	struct io_uring_sqe *sqes;
+	size_t sqes_size;
+	unsigned int *sq_tail;
+	unsigned int *sq_mask;
+	unsigned int *sq_array;
+	unsigned int *cq_head;
+	unsigned int *cq_tail;
+	unsigned int *cq_mask;
+	struct io_uring_cqe *cqes;
+};
+
+static int ring_setup(struct ring *ring, unsigned int entries)
+{
+	struct io_uring_params params;
+	size_t sq_size, cq_size;
+	char *ptr;
+
+	memset(&params, 0, sizeof(params));
+	ring->fd = syscall(SYS_io_uring_setup, entries, &params);
+	if (ring->fd < 0)
+		return -1;
+	ring->sq_entries = params.sq_entries;
+	ring->cq_entries = params.cq_entries;
+
+	// With `IORING_FEAT_SINGLE_MMAP`, the SQ ring and the CQ ring share a
+	// single mapping.
+	sq_size = params.sq_off.array +
+		  params.sq_entries * sizeof(unsigned int);
+	cq_size = params.cq_off.cqes +
+		  params.cq_entries * sizeof(struct io_uring_cqe);
+	ring->ring_size = sq_size > cq_size ? sq_size : cq_size;
+	ptr = mmap(NULL, ring->ring_size, PROT_READ | PROT_WRITE, MAP_SHARED,
+		   ring->fd, IORING_OFF_SQ_RING);
+	if (ptr == MAP_FAILED)
+		goto err_close;
+	ring->ring_ptr = ptr;
+
+	ring->sqes_size = params.sq_entries * sizeof(struct io_uring_sqe);
+	ring->sqes = mmap(NULL, ring->sqes_size, PROT_READ | PROT_WRITE,
+			  MAP_SHARED, ring->fd, IORING_OFF_SQES);
+	if (ring->sqes == MAP_FAILED)
+		goto err_unmap;
+
+	ring->sq_tail = (void *)(ptr + params.sq_off.tail);
+	ring->sq_mask = (void *)(ptr + params.sq_off.ring_mask);
+	ring->sq_array = (void *)(ptr + params.sq_off.array);
+	ring->cq_head = (void *)(ptr + params.cq_off.head);
+	ring->cq_tail = (void *)(ptr + params.cq_off.tail);
+	ring->cq_mask = (void *)(ptr + params.cq_off.ring_mask);
+	ring->cqes = (void *)(ptr + params.cq_off.cqes);
+
+	return 0;
+
+err_unmap:
+	munmap(ptr, ring->ring_size);
+err_close:
+	close(ring->fd);
+	return -1;
+}
+
+static void ring_unmap(struct ring *ring)
+{
+	munmap(ring->sqes, ring->sqes_size);
+	munmap(ring->ring_ptr, ring->ring_size);
+}
+
+static int ring_submit(struct ring *ring, const struct io_uring_sqe *sqe)
+{
+	unsigned int tail = *ring->sq_tail;
+	unsigned int index = tail & *ring->sq_mask;
+
+	ring->sqes[index] = *sqe;
+	ring->sq_array[index] = index;
+	__atomic_store_n(ring->sq_tail, tail + 1, __ATOMIC_RELEASE);
+
+	return syscall(SYS_io_uring_enter, ring->fd, 1, 0, 0, NULL, 0);
+}
+
+static int ring_wait(struct ring *ring, struct io_uring_cqe *cqe)
+{
+	unsigned int head = *ring->cq_head;
+
+	if (syscall(SYS_io_uring_enter, ring->fd, 0, 1, IORING_ENTER_GETEVENTS,
+		    NULL, 0) < 0)
+		return -1;
+	if (__atomic_load_n(ring->cq_tail, __ATOMIC_ACQUIRE) == head) {
+		errno = EAGAIN;
+		return -1;
+	}
+
+	*cqe = ring->cqes[head & *ring->cq_mask];
+	__atomic_store_n(ring->cq_head, head + 1, __ATOMIC_RELEASE);
+
+	return 0;
+}
+
+static int read_nonblock(int fd, void *buf, size_t len)
+{
+	int flags, ret;
+
+	flags = fcntl(fd, F_GETFL);
+	if (flags < 0)
+		return -1;
+	if (fcntl(fd, F_SETFL, flags | O_NONBLOCK) < 0)
+		return -1;
+	ret = read(fd, buf, len);
+	fcntl(fd, F_SETFL, flags);
+
+	return ret;
+}
+
+#define FILE_NAME "/tmp/io_uring_test"
+
+static struct ring ring;
+static int file_fd;
+static int pipe_fds[2];
+
+// The buffers are larger than the kernel buffer of a request, so the data are
+// transferred in several chunks.
+static char write_buf[1 << 17];
+static char read_buf[1 << 17];
+
+FN_TEST(setup_invalid)
+{
+	struct io_uring_params params;
+
+	memset(&params, 0, sizeof(params));
+	TEST_ERRNO(syscall(SYS_io_uring_setup, 0, &params), EINVAL);
+
+	memset(&params, 0, sizeof(params));
+	params.flags = IORING_SETUP_CQSIZE;
+	params.cq_entries = 2;
+	TEST_ERRNO(syscall(SYS_io_uring_setup, 4, &params), EINVAL);
+}
+END_TEST()
+
+FN_SETUP(setup)
+{
+	size_t i;
+
+	CHECK(ring_setup(&ring, 3));
+	file_fd = CHECK(open(FILE_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	CHECK(pipe(pipe_fds));
+
+	for (i = 0; i < sizeof(write_buf); i++)
+		write_buf[i] = i % 251;
+}
+END_SETUP()
+
+FN_TEST(entries)
+{
+	// The numbers of entries are rounded up to powers of two.
+	TEST_RES(ring.sq_entries, _ret == 4);
+	TEST_RES(ring.cq_entries, _ret == 8);
+}
+END_TEST()
+
+FN_TEST(nop)
+{
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_NOP, .user_data = 1 };
+	struct io_uring_cqe cqe;
+
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe), cqe.user_data == 1 && cqe.res == 0);
+}
+END_TEST()
+
+FN_TEST(submit_more_than_sq)
+{
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_NOP, .user_data = 13 };
+	struct io_uring_cqe cqe;
+	unsigned int tail = *ring.sq_tail;
+	unsigned int i;
+
+	for (i = 0; i < ring.sq_entries; i++) {
+		ring.sqes[i] = sqe;
+		ring.sq_array[i] = i;
+	}
+
+	// The tail is ahead of the head by more than the size of the SQ, but at
+	// most one SQ of SQEs is submitted at once.
+	__atomic_store_n(ring.sq_tail, tail + ring.sq_entries + 2,
+			 __ATOMIC_RELEASE);
+	TEST_RES(syscall(SYS_io_uring_enter, ring.fd, 100, 0, 0, NULL, 0),
+		 _ret == ring.sq_entries);
+	TEST_RES(syscall(SYS_io_uring_enter, ring.fd, 100, 0, 0, NULL, 0),
+		 _ret == 2);
+
+	for (i = 0; i < ring.sq_entries + 2; i++)
+		TEST_RES(ring_wait(&ring, &cqe),
+			 cqe.user_data == 13 && cqe.res == 0);
+}
+END_TEST()
+
+FN_TEST(read_write)
+{
+	struct io_uring_sqe sqe;
+	struct io_uring_cqe cqe;
+
+	sqe = (struct io_uring_sqe){ .opcode = IORING_OP_WRITE,
+				     .fd = file_fd,
+				     .addr = (uintptr_t)write_buf,
+				     .len = sizeof(write_buf),
+				     .off = 0,
+				     .user_data = 2 };
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 2 && cqe.res == sizeof(write_buf));
+
+	sqe = (struct io_uring_sqe){ .opcode = IORING_OP_READ,
+				     .fd = file_fd,
+				     .addr = (uintptr_t)read_buf,
+				     .len = sizeof(read_buf),
+				     .off = 0,
+				     .user_data = 3 };
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 3 && cqe.res == sizeof(read_buf) &&
+			 memcmp(read_buf, write_buf, sizeof(read_buf)) == 0);
+
+	// The read is short at the end of the file.
+	sqe.off = sizeof(write_buf) - 5;
+	sqe.user_data = 4;
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 4 && cqe.res == 5 &&
+			 memcmp(read_buf, write_buf + sizeof(write_buf) - 5,
+				5) == 0);
+}
+END_TEST()
+
+FN_TEST(readv)
+{
+	struct iovec iov[2] = {
+		{ .iov_base = read_buf, .iov_len = 100 },
+		{ .iov_base = read_buf + 100,
+		  .iov_len = sizeof(read_buf) - 100 },
+	};
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_READV,
+				    .fd = file_fd,
+				    .addr = (uintptr_t)iov,
+				    .len = 2,
+				    .off = 1,
+				    .user_data = 5 };
+	struct io_uring_cqe cqe;
+
+	memset(read_buf, 0, sizeof(read_buf));
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 5 && cqe.res == sizeof(read_buf) - 1 &&
+			 memcmp(read_buf, write_buf + 1,
+				sizeof(read_buf) - 1) == 0);
+}
+END_TEST()
+
+FN_TEST(fixed_files)
+{
+	int fds[2] = { -1, file_fd };
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_READ,
+				    .flags = IOSQE_FIXED_FILE,
+				    .fd = 1,
+				    .addr = (uintptr_t)read_buf,
+				    .len = 16,
+				    .off = 0,
+				    .user_data = 6 };
+	struct io_uring_cqe cqe;
+
+	TEST_SUCC(syscall(SYS_io_uring_register, ring.fd,
+			  IORING_REGISTER_FILES, fds, 2));
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_REGISTER_FILES, fds, 2),
+		   EBUSY);
+
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 6 && cqe.res == 16 &&
+			 memcmp(read_buf, write_buf, 16) == 0);
+
+	// The sparse slot does not contain a file.
+	sqe.fd = 0;
+	sqe.user_data = 7;
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 7 && cqe.res == -EBADF);
+
+	TEST_SUCC(syscall(SYS_io_uring_register, ring.fd,
+			  IORING_UNREGISTER_FILES, NULL, 0));
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_UNREGISTER_FILES, NULL, 0),
+		   ENXIO);
+
+	// An io_uring file cannot be registered.
+	fds[0] = ring.fd;
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_REGISTER_FILES, fds, 2),
+		   EBADF);
+}
+END_TEST()
+
+FN_TEST(eventfd)
+{
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_NOP, .user_data = 8 };
+	struct io_uring_cqe cqe;
+	uint64_t value;
+	int efd;
+
+	// Only an eventfd can be registered.
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_REGISTER_EVENTFD, &pipe_fds[0], 1),
+		   EBADF);
+
+	efd = TEST_SUCC(eventfd(0, EFD_NONBLOCK));
+	TEST_SUCC(syscall(SYS_io_uring_register, ring.fd,
+			  IORING_REGISTER_EVENTFD, &efd, 1));
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_REGISTER_EVENTFD, &efd, 1),
+		   EBUSY);
+
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe), cqe.user_data == 8);
+	TEST_RES(read(efd, &value, sizeof(value)),
+		 _ret == sizeof(value) && value == 1);
+
+	TEST_SUCC(syscall(SYS_io_uring_register, ring.fd,
+			  IORING_UNREGISTER_EVENTFD, NULL, 0));
+	TEST_ERRNO(syscall(SYS_io_uring_register, ring.fd,
+			   IORING_UNREGISTER_EVENTFD, NULL, 0),
+		   ENXIO);
+
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe), cqe.user_data == 8);
+	TEST_ERRNO(read(efd, &value, sizeof(value)), EAGAIN);
+
+	TEST_SUCC(close(efd));
+}
+END_TEST()
+
+FN_TEST(async_cancel)
+{
+	struct io_uring_sqe sqe;
+	struct io_uring_cqe cqe;
+	int read_res = 0, cancel_res = 0;
+	int i;
+
+	// The read blocks because the pipe is empty.
+	sqe = (struct io_uring_sqe){ .opcode = IORING_OP_READ,
+				     .fd = pipe_fds[0],
+				     .addr = (uintptr_t)read_buf,
+				     .len = 16,
+				     .off = -1,
+				     .user_data = 9 };
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+
+	sqe = (struct io_uring_sqe){ .opcode = IORING_OP_ASYNC_CANCEL,
+				     .addr = 9,
+				     .user_data = 10 };
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+
+	for (i = 0; i < 2; i++) {
+		TEST_RES(ring_wait(&ring, &cqe),
+			 cqe.user_data == 9 || cqe.user_data == 10);
+		if (cqe.user_data == 9)
+			read_res = cqe.res;
+		else
+			cancel_res = cqe.res;
+	}
+	// The read is either cancelled before it starts, or interrupted while
+	// it is blocking.
+	TEST_RES(read_res,
+		 (cancel_res == 0 && read_res == -ECANCELED) ||
+			 (cancel_res == -EALREADY && read_res == -EINTR));
+
+	// The cancelled read does not consume the data written later.
+	TEST_RES(write(pipe_fds[1], "x", 1), _ret == 1);
+	TEST_RES(read_nonblock(pipe_fds[0], read_buf, sizeof(read_buf)),
+		 _ret == 1 && read_buf[0] == 'x');
+
+	// The request to cancel does not exist.
+	sqe.user_data = 11;
+	TEST_RES(ring_submit(&ring, &sqe), _ret == 1);
+	TEST_RES(ring_wait(&ring, &cqe),
+		 cqe.user_data == 11 && cqe.res == -ENOENT);
+}
+END_TEST()
+
+FN_TEST(close_in_flight)
+{
+	struct io_uring_sqe sqe = { .opcode = IORING_OP_READ,
+				    .fd = pipe_fds[0],
+				    .addr = (uintptr_t)read_buf,
+				    .len = 16,
+				    .off = -1,
+				    .user_data = 12 };
+	struct ring ring2;
+
+	TEST_SUCC(ring_setup(&ring2, 4));
+	TEST_RES(ring_submit(&ring2, &sqe), _ret == 1);
+
+	// Closing the ring cancels the blocking read instead of waiting for it.
+	ring_unmap(&ring2);
+	TEST_SUCC(close(ring2.fd));
+
+	// Linux cancels the requests asynchronously after the ring is closed.
+	usleep(100 * 1000);
+
+	TEST_RES(write(pipe_fds[1], "y", 1), _ret == 1);
+	TEST_RES(read_nonblock(pipe_fds[0], read_buf, sizeof(read_buf)),
+		 _ret == 1 && read_buf[0] == 'y');
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	ring_unmap(&ring);
+	CHECK(close(ring.fd));
+	CHECK(close(file_fd));
+	CHECK(unlink(FILE_NAME));
+	CHECK(close(pipe_fds[0]));
+	CHECK(close(pipe_fds[1]));
+}
+END_SETUP()
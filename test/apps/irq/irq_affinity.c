@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <dirent.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/sysinfo.h>
+
+#include "../network/test.h"
+
+#define IRQ_ROOT "/sys/kernel/irq"
+#define PCI_DEVICES "/sys/devices/pci0000:00"
+#define CHIP_PREFIX "PCI-MSIX-"
+
+static char irq_name[16];
+static char slot_name[64];
+
+static int read_attr(const char *attr, char *buf, size_t len)
+{
+	char path[256];
+	int fd;
+	ssize_t n;
+
+	snprintf(path, sizeof(path), IRQ_ROOT "/%s/%s", irq_name, attr);
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	n = read(fd, buf, len - 1);
+	close(fd);
+	if (n < 0)
+		return -1;
+	buf[n] = '\0';
+	return n;
+}
+
+static int write_attr(const char *attr, const char *value)
+{
+	char path[256];
+	int fd;
+	ssize_t n;
+
+	snprintf(path, sizeof(path), IRQ_ROOT "/%s/%s", irq_name, attr);
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	n = write(fd, value, strlen(value));
+	close(fd);
+	return n;
+}
+
+FN_SETUP(find_irq)
+{
+	DIR *dir;
+	struct dirent *entry;
+	char chip_name[128];
+
+	dir = opendir(IRQ_ROOT);
+	CHECK_WITH(dir == NULL ? -1 : 0, _ret == 0);
+	while ((entry = readdir(dir)) != NULL) {
+		if (entry->d_name[0] != '.') {
+			snprintf(irq_name, sizeof(irq_name), "%s",
+				 entry->d_name);
+			break;
+		}
+	}
+	closedir(dir);
+
+	// All the virtio devices have some IRQ lines bound to MSI-X vectors.
+	CHECK_WITH(irq_name[0], _ret != 0);
+	CHECK(read_attr("chip_name", chip_name, sizeof(chip_name)));
+	CHECK_WITH(strncmp(chip_name, CHIP_PREFIX, strlen(CHIP_PREFIX)),
+		   _ret == 0);
+	snprintf(slot_name, sizeof(slot_name), "%s",
+		 chip_name + strlen(CHIP_PREFIX));
+	slot_name[strcspn(slot_name, "\n")] = '\0';
+}
+END_SETUP()
+
+FN_TEST(read_attrs)
+{
+	char buf[128];
+
+	TEST_RES(read_attr("type", buf, sizeof(buf)),
+		 strcmp(buf, "edge\n") == 0);
+	TEST_RES(read_attr("smp_affinity", buf, sizeof(buf)), _ret > 1);
+	TEST_RES(read_attr("effective_affinity", buf, sizeof(buf)), _ret > 1);
+	TEST_RES(read_attr("affinity_hint", buf, sizeof(buf)), _ret > 1);
+}
+END_TEST()
+
+FN_TEST(set_affinity)
+{
+	char buf[128];
+	int last_cpu = get_nprocs() - 1;
+	char expected[32];
+
+	snprintf(expected, sizeof(expected), "%d\n", last_cpu);
+
+	TEST_SUCC(write_attr("smp_affinity_list", expected));
+	TEST_RES(read_attr("smp_affinity_list", buf, sizeof(buf)),
+		 strcmp(buf, expected) == 0);
+	TEST_RES(read_attr("effective_affinity_list", buf, sizeof(buf)),
+		 strcmp(buf, expected) == 0);
+
+	TEST_SUCC(write_attr("smp_affinity", "1"));
+	TEST_RES(read_attr("smp_affinity_list", buf, sizeof(buf)),
+		 strcmp(buf, "0\n") == 0);
+	TEST_RES(read_attr("effective_affinity_list", buf, sizeof(buf)),
+		 strcmp(buf, "0\n") == 0);
+}
+END_TEST()
+
+FN_TEST(set_invalid_affinity)
+{
+	char buf[128];
+	char value[32];
+
+	snprintf(value, sizeof(value), "%d", get_nprocs());
+
+	TEST_ERRNO(write_attr("smp_affinity_list", value), EIO);
+	TEST_ERRNO(write_attr("smp_affinity_list", "1-0"), EIO);
+	TEST_ERRNO(write_attr("smp_affinity", "zz"), EIO);
+	TEST_ERRNO(write_attr("smp_affinity", "0"), EIO);
+	TEST_RES(read_attr("smp_affinity_list", buf, sizeof(buf)),
+		 strcmp(buf, "0\n") == 0);
+}
+END_TEST()
+
+FN_TEST(msi_irqs)
+{
+	char path[256];
+	char buf[16];
+	int fd;
+
+	snprintf(path, sizeof(path), PCI_DEVICES "/%s/msi_irqs/%s", slot_name,
+		 irq_name);
+	fd = TEST_SUCC(open(path, O_RDONLY));
+	TEST_RES(read(fd, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "msix\n", 5) == 0);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
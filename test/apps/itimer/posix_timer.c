@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <pthread.h>
+#include <signal.h>
+#include <time.h>
+#include <unistd.h>
+#include <sys/signalfd.h>
+#include <sys/syscall.h>
+
+#include "../network/test.h"
+
+// The raw system calls are used, since the C library may not expose the
+// kernel timer IDs.
+#define timer_create_raw(clockid, sev, timerid) \
+	syscall(SYS_timer_create, clockid, sev, timerid)
+#define timer_settime_raw(timerid, flags, new_value, old_value) \
+	syscall(SYS_timer_settime, timerid, flags, new_value, old_value)
+#define timer_getoverrun_raw(timerid) syscall(SYS_timer_getoverrun, timerid)
+#define timer_delete_raw(timerid) syscall(SYS_timer_delete, timerid)
+
+static int sfd;
+static struct signalfd_siginfo info;
+
+static int arm_timer(int timerid, long value_ms, long interval_ms)
+{
+	struct itimerspec its = {
+		.it_value = { .tv_sec = value_ms / 1000,
+			      .tv_nsec = value_ms % 1000 * 1000000 },
+		.it_interval = { .tv_sec = interval_ms / 1000,
+				 .tv_nsec = interval_ms % 1000 * 1000000 },
+	};
+
+	return timer_settime_raw(timerid, 0, &its, NULL);
+}
+
+FN_SETUP(signalfd)
+{
+	sigset_t mask;
+
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR1);
+	sigaddset(&mask, SIGALRM);
+	CHECK(sigprocmask(SIG_BLOCK, &mask, NULL));
+
+	sfd = CHECK(signalfd(-1, &mask, SFD_NONBLOCK));
+}
+END_SETUP()
+
+FN_TEST(invalid_args)
+{
+	struct sigevent sev = { .sigev_notify = SIGEV_SIGNAL };
+	int timerid;
+
+	sev.sigev_signo = SIGUSR1;
+	TEST_ERRNO(timer_create_raw(100, &sev, &timerid), EINVAL);
+
+	sev.sigev_signo = 0;
+	TEST_ERRNO(timer_create_raw(CLOCK_MONOTONIC, &sev, &timerid), EINVAL);
+	sev.sigev_signo = 65;
+	TEST_ERRNO(timer_create_raw(CLOCK_MONOTONIC, &sev, &timerid), EINVAL);
+
+	sev.sigev_notify = SIGEV_THREAD_ID;
+	sev.sigev_signo = SIGUSR1;
+	sev._sigev_un._tid = 0x7fffffff;
+	TEST_ERRNO(timer_create_raw(CLOCK_MONOTONIC, &sev, &timerid), EINVAL);
+
+	TEST_ERRNO(arm_timer(12345, 10, 0), EINVAL);
+	TEST_ERRNO(timer_getoverrun_raw(12345), EINVAL);
+	TEST_ERRNO(timer_delete_raw(12345), EINVAL);
+}
+END_TEST()
+
+FN_TEST(overrun)
+{
+	struct sigevent sev = {
+		.sigev_notify = SIGEV_SIGNAL,
+		.sigev_signo = SIGUSR1,
+		.sigev_value = { .sival_int = 42 },
+	};
+	int timerid;
+
+	TEST_SUCC(timer_create_raw(CLOCK_MONOTONIC, &sev, &timerid));
+	TEST_SUCC(arm_timer(timerid, 10, 10));
+
+	// Only one signal is queued, and the other expirations are overruns.
+	usleep(200 * 1000);
+	TEST_RES(read(sfd, &info, sizeof(info)),
+		 _ret == sizeof(info) && info.ssi_signo == SIGUSR1 &&
+			 info.ssi_code == SI_TIMER && info.ssi_int == 42 &&
+			 info.ssi_tid == timerid && info.ssi_overrun >= 5);
+	TEST_RES(timer_getoverrun_raw(timerid), _ret == info.ssi_overrun);
+
+	TEST_SUCC(arm_timer(timerid, 0, 0));
+	TEST_RES(timer_getoverrun_raw(timerid), _ret == 0);
+	TEST_SUCC(timer_delete_raw(timerid));
+
+	// Drain the signal sent before the timer is disarmed.
+	while (read(sfd, &info, sizeof(info)) > 0)
+		;
+}
+END_TEST()
+
+FN_TEST(default_sigevent)
+{
+	int timerid;
+
+	// Without a `sigevent`, `SIGALRM` is sent with the timer ID as the value.
+	TEST_SUCC(timer_create_raw(CLOCK_REALTIME, NULL, &timerid));
+	TEST_SUCC(arm_timer(timerid, 10, 0));
+	usleep(100 * 1000);
+	TEST_RES(read(sfd, &info, sizeof(info)),
+		 _ret == sizeof(info) && info.ssi_signo == SIGALRM &&
+			 info.ssi_code == SI_TIMER && info.ssi_int == timerid);
+	TEST_SUCC(timer_delete_raw(timerid));
+}
+END_TEST()
+
+FN_TEST(cputime_clock)
+{
+	struct sigevent sev = {
+		.sigev_notify = SIGEV_SIGNAL,
+		.sigev_signo = SIGUSR1,
+	};
+	int timerid;
+
+	TEST_SUCC(timer_create_raw(CLOCK_PROCESS_CPUTIME_ID, &sev, &timerid));
+	TEST_SUCC(arm_timer(timerid, 50, 0));
+
+	// The timer expires after the process consumes enough CPU time.
+	for (;;) {
+		sigset_t pending;
+
+		sigpending(&pending);
+		if (sigismember(&pending, SIGUSR1))
+			break;
+	}
+	TEST_RES(read(sfd, &info, sizeof(info)),
+		 _ret == sizeof(info) && info.ssi_code == SI_TIMER &&
+			 info.ssi_tid == timerid);
+	TEST_SUCC(timer_delete_raw(timerid));
+}
+END_TEST()
+
+static volatile pid_t signaled_tid;
+
+static void handler(int sig, siginfo_t *si, void *unused)
+{
+	if (si->si_code == SI_TIMER)
+		signaled_tid = syscall(SYS_gettid);
+}
+
+static pid_t thread_tid;
+
+static void *thread_func(void *arg)
+{
+	sigset_t mask;
+
+	thread_tid = syscall(SYS_gettid);
+
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR2);
+	pthread_sigmask(SIG_UNBLOCK, &mask, NULL);
+
+	while (signaled_tid == 0)
+		usleep(1000);
+	return NULL;
+}
+
+FN_TEST(thread_id)
+{
+	struct sigaction sa = { .sa_flags = SA_SIGINFO, .sa_sigaction = handler };
+	struct sigevent sev = {
+		.sigev_notify = SIGEV_THREAD_ID,
+		.sigev_signo = SIGUSR2,
+	};
+	sigset_t mask;
+	pthread_t thread;
+	int timerid;
+
+	// Block the signal in the main thread, so only the target thread can
+	// handle it.
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR2);
+	TEST_SUCC(sigprocmask(SIG_BLOCK, &mask, NULL));
+	TEST_SUCC(sigaction(SIGUSR2, &sa, NULL));
+
+	TEST_RES(pthread_create(&thread, NULL, thread_func, NULL), _ret == 0);
+	while (thread_tid == 0)
+		usleep(1000);
+
+	sev._sigev_un._tid = thread_tid;
+	TEST_SUCC(timer_create_raw(CLOCK_MONOTONIC, &sev, &timerid));
+	TEST_SUCC(arm_timer(timerid, 10, 0));
+
+	TEST_RES(pthread_join(thread, NULL), _ret == 0);
+	TEST_RES(signaled_tid, _ret == thread_tid);
+	TEST_SUCC(timer_delete_raw(timerid));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(sfd));
+}
+END_SETUP()
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdint.h>
+#include <time.h>
+#include <unistd.h>
+#include <sys/timerfd.h>
+
+FN_TEST(timerfd_invalid)
+{
+	int tfd;
+	struct itimerspec its = { .it_value = { .tv_nsec = 1000000 } };
+
+	TEST_ERRNO(timerfd_create(CLOCK_PROCESS_CPUTIME_ID, 0), EINVAL);
+	TEST_ERRNO(timerfd_create(CLOCK_MONOTONIC, 0x4), EINVAL);
+
+	tfd = TEST_SUCC(timerfd_create(CLOCK_MONOTONIC, 0));
+	TEST_ERRNO(timerfd_settime(tfd, 0x4, &its, NULL), EINVAL);
+	TEST_SUCC(close(tfd));
+}
+END_TEST()
+
+FN_TEST(timerfd_relative)
+{
+	int tfd;
+	uint64_t ticks;
+	struct itimerspec its = { .it_value = { .tv_nsec = 10000000 } };
+
+	tfd = TEST_SUCC(timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK));
+	TEST_ERRNO(read(tfd, &ticks, sizeof(ticks)), EAGAIN);
+
+	TEST_SUCC(timerfd_settime(tfd, 0, &its, NULL));
+	TEST_SUCC(fcntl(tfd, F_SETFL, 0));
+	TEST_RES(read(tfd, &ticks, sizeof(ticks)),
+		 _ret == sizeof(ticks) && ticks == 1);
+
+	TEST_SUCC(close(tfd));
+}
+END_TEST()
+
+FN_TEST(timerfd_absolute)
+{
+	int tfd;
+	uint64_t ticks;
+	struct itimerspec its = {};
+
+	tfd = TEST_SUCC(timerfd_create(CLOCK_REALTIME, 0));
+
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME, &its.it_value));
+	its.it_value.tv_nsec += 10000000;
+	if (its.it_value.tv_nsec >= 1000000000) {
+		its.it_value.tv_sec += 1;
+		its.it_value.tv_nsec -= 1000000000;
+	}
+	TEST_SUCC(timerfd_settime(
+		tfd, TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET, &its, NULL));
+	TEST_RES(read(tfd, &ticks, sizeof(ticks)),
+		 _ret == sizeof(ticks) && ticks == 1);
+
+	TEST_SUCC(close(tfd));
+}
+END_TEST()
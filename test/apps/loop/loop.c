@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <linux/loop.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#ifndef LOOP_CONFIGURE
+#define LOOP_CONFIGURE 0x4C0A
+
+struct loop_config {
+	__u32 fd;
+	__u32 block_size;
+	struct loop_info64 info;
+	__u64 __reserved[8];
+};
+#endif
+
+#define IMAGE_FILE "/ext2/loop_test.img"
+#define IMAGE_SIZE (64 * 1024)
+#define IMAGE_OFFSET 4096
+#define NEW_INDEX 100
+
+static int ctl_fd;
+static int loop_index;
+static char dev_path[32];
+static char buf[64];
+
+FN_SETUP(image)
+{
+	int fd;
+
+	fd = CHECK(open(IMAGE_FILE, O_CREAT | O_TRUNC | O_RDWR, 0644));
+	CHECK(ftruncate(fd, IMAGE_SIZE));
+	CHECK(close(fd));
+}
+END_SETUP()
+
+FN_SETUP(free_device)
+{
+	ctl_fd = CHECK(open("/dev/loop-control", O_RDWR));
+	loop_index = CHECK(ioctl(ctl_fd, LOOP_CTL_GET_FREE));
+	snprintf(dev_path, sizeof(dev_path), "/dev/loop%d", loop_index);
+}
+END_SETUP()
+
+static int configure(int loop_fd, int file_fd, __u64 offset, __u32 flags)
+{
+	struct loop_config config;
+
+	memset(&config, 0, sizeof(config));
+	config.fd = file_fd;
+	config.info.lo_offset = offset;
+	config.info.lo_flags = flags;
+	strcpy((char *)config.info.lo_file_name, IMAGE_FILE);
+
+	return ioctl(loop_fd, LOOP_CONFIGURE, &config);
+}
+
+FN_TEST(configure)
+{
+	struct loop_info64 info;
+	struct stat stat_buf;
+	int loop_fd, file_fd;
+
+	TEST_RES(stat(dev_path, &stat_buf),
+		 S_ISBLK(stat_buf.st_mode) && major(stat_buf.st_rdev) == 7 &&
+			 minor(stat_buf.st_rdev) == loop_index);
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	file_fd = TEST_SUCC(open(IMAGE_FILE, O_RDWR));
+	TEST_ERRNO(ioctl(loop_fd, LOOP_GET_STATUS64, &info), ENXIO);
+
+	TEST_SUCC(configure(loop_fd, file_fd, IMAGE_OFFSET, 0));
+	TEST_ERRNO(configure(loop_fd, file_fd, 0, 0), EBUSY);
+	TEST_RES(ioctl(loop_fd, LOOP_GET_STATUS64, &info),
+		 info.lo_number == loop_index && info.lo_offset == IMAGE_OFFSET &&
+			 info.lo_flags == 0 &&
+			 strcmp((char *)info.lo_file_name, IMAGE_FILE) == 0);
+
+	// The device is bound, so it is not free and cannot be removed.
+	TEST_RES(ioctl(ctl_fd, LOOP_CTL_GET_FREE), _ret != loop_index);
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_REMOVE, loop_index), EBUSY);
+
+	// `LO_FLAGS_READ_ONLY` cannot be changed with `LOOP_SET_STATUS64`.
+	info.lo_flags = LO_FLAGS_READ_ONLY | LO_FLAGS_AUTOCLEAR;
+	TEST_SUCC(ioctl(loop_fd, LOOP_SET_STATUS64, &info));
+	TEST_RES(ioctl(loop_fd, LOOP_GET_STATUS64, &info),
+		 info.lo_flags == LO_FLAGS_AUTOCLEAR);
+	info.lo_flags = 0;
+	TEST_SUCC(ioctl(loop_fd, LOOP_SET_STATUS64, &info));
+	TEST_RES(ioctl(loop_fd, LOOP_GET_STATUS64, &info), info.lo_flags == 0);
+
+	TEST_SUCC(ioctl(loop_fd, LOOP_CLR_FD));
+	TEST_ERRNO(ioctl(loop_fd, LOOP_GET_STATUS64, &info), ENXIO);
+	TEST_ERRNO(ioctl(loop_fd, LOOP_CLR_FD), ENXIO);
+
+	TEST_SUCC(close(file_fd));
+	TEST_SUCC(close(loop_fd));
+}
+END_TEST()
+
+FN_TEST(read_only)
+{
+	struct loop_info64 info;
+	int loop_fd, file_fd;
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	file_fd = TEST_SUCC(open(IMAGE_FILE, O_RDONLY));
+
+	// The device is read-only if the file is opened read-only.
+	TEST_SUCC(ioctl(loop_fd, LOOP_SET_FD, file_fd));
+	TEST_RES(ioctl(loop_fd, LOOP_GET_STATUS64, &info),
+		 info.lo_offset == 0 && info.lo_flags == LO_FLAGS_READ_ONLY);
+	TEST_SUCC(ioctl(loop_fd, LOOP_CLR_FD));
+
+	TEST_SUCC(close(file_fd));
+	TEST_SUCC(close(loop_fd));
+}
+END_TEST()
+
+FN_TEST(autoclear)
+{
+	struct loop_info64 info;
+	int loop_fd, loop_fd2, file_fd;
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	file_fd = TEST_SUCC(open(IMAGE_FILE, O_RDWR));
+	TEST_SUCC(configure(loop_fd, file_fd, 0, LO_FLAGS_AUTOCLEAR));
+	TEST_SUCC(close(file_fd));
+
+	// The device stays bound until it is closed for the last time.
+	loop_fd2 = TEST_SUCC(open(dev_path, O_RDWR));
+	TEST_SUCC(close(loop_fd));
+	TEST_RES(ioctl(loop_fd2, LOOP_GET_STATUS64, &info),
+		 info.lo_flags == LO_FLAGS_AUTOCLEAR);
+	TEST_SUCC(close(loop_fd2));
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	TEST_ERRNO(ioctl(loop_fd, LOOP_GET_STATUS64, &info), ENXIO);
+
+	// `LOOP_CLR_FD` sets `LO_FLAGS_AUTOCLEAR` if the device is open elsewhere.
+	file_fd = TEST_SUCC(open(IMAGE_FILE, O_RDWR));
+	TEST_SUCC(configure(loop_fd, file_fd, 0, 0));
+	TEST_SUCC(close(file_fd));
+	loop_fd2 = TEST_SUCC(open(dev_path, O_RDWR));
+	TEST_SUCC(ioctl(loop_fd2, LOOP_CLR_FD));
+	TEST_RES(ioctl(loop_fd, LOOP_GET_STATUS64, &info),
+		 info.lo_flags == LO_FLAGS_AUTOCLEAR);
+	TEST_SUCC(close(loop_fd2));
+	TEST_SUCC(close(loop_fd));
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	TEST_ERRNO(ioctl(loop_fd, LOOP_GET_STATUS64, &info), ENXIO);
+	TEST_SUCC(close(loop_fd));
+}
+END_TEST()
+
+FN_TEST(invalid_file)
+{
+	int loop_fd, dir_fd;
+
+	loop_fd = TEST_SUCC(open(dev_path, O_RDWR));
+	dir_fd = TEST_SUCC(open("/ext2", O_RDONLY | O_DIRECTORY));
+
+	TEST_ERRNO(ioctl(loop_fd, LOOP_SET_FD, dir_fd), EINVAL);
+	TEST_ERRNO(ioctl(loop_fd, LOOP_SET_FD, -1), EBADF);
+
+	TEST_SUCC(close(dir_fd));
+	TEST_SUCC(close(loop_fd));
+}
+END_TEST()
+
+FN_TEST(add_remove)
+{
+	char path[32];
+	int len, fd;
+
+	snprintf(path, sizeof(path), "/dev/loop%d", NEW_INDEX);
+
+	TEST_RES(ioctl(ctl_fd, LOOP_CTL_ADD, NEW_INDEX), _ret == NEW_INDEX);
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_ADD, NEW_INDEX), EEXIST);
+	TEST_SUCC(access(path, F_OK));
+
+	fd = TEST_SUCC(open("/sys/class/block/loop100/dev", O_RDONLY));
+	len = TEST_SUCC(read(fd, buf, sizeof(buf) - 1));
+	buf[len] = '\0';
+	TEST_RES(strcmp(buf, "7:100\n"), _ret == 0);
+	TEST_SUCC(close(fd));
+
+	// The device cannot be removed while it is open.
+	fd = TEST_SUCC(open(path, O_RDWR));
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_REMOVE, NEW_INDEX), EBUSY);
+	TEST_SUCC(close(fd));
+
+	TEST_SUCC(ioctl(ctl_fd, LOOP_CTL_REMOVE, NEW_INDEX));
+	TEST_ERRNO(ioctl(ctl_fd, LOOP_CTL_REMOVE, NEW_INDEX), ENODEV);
+	TEST_ERRNO(access(path, F_OK), ENOENT);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(ctl_fd));
+	CHECK(unlink(IMAGE_FILE));
+}
+END_SETUP()
@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <linux/membarrier.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#ifndef MEMBARRIER_CMD_GET_REGISTRATIONS
+#define MEMBARRIER_CMD_GET_REGISTRATIONS (1 << 9)
+#endif
+
+static int membarrier(int cmd, unsigned int flags, int cpu_id)
+{
+	return syscall(SYS_membarrier, cmd, flags, cpu_id);
+}
+
+FN_TEST(query)
+{
+	int supported;
+
+	supported = TEST_SUCC(membarrier(MEMBARRIER_CMD_QUERY, 0, 0));
+	TEST_RES(supported & MEMBARRIER_CMD_GLOBAL, _ret != 0);
+	TEST_RES(supported & MEMBARRIER_CMD_PRIVATE_EXPEDITED, _ret != 0);
+	TEST_RES(supported & MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE,
+		 _ret != 0);
+}
+END_TEST()
+
+FN_TEST(invalid_args)
+{
+	TEST_ERRNO(membarrier(MEMBARRIER_CMD_GLOBAL, 1, 0), EINVAL);
+	TEST_ERRNO(membarrier(MEMBARRIER_CMD_GLOBAL |
+				      MEMBARRIER_CMD_GLOBAL_EXPEDITED,
+			      0, 0),
+		   EINVAL);
+	TEST_ERRNO(membarrier(-1, 0, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(global)
+{
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_GLOBAL, 0, 0));
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_GLOBAL_EXPEDITED, 0, 0));
+}
+END_TEST()
+
+FN_TEST(private_expedited)
+{
+	TEST_RES(membarrier(MEMBARRIER_CMD_GET_REGISTRATIONS, 0, 0), _ret == 0);
+
+	TEST_ERRNO(membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0, 0), EPERM);
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED, 0, 0));
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0, 0));
+
+	TEST_ERRNO(membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE, 0, 0),
+		   EPERM);
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE,
+			     0, 0));
+	TEST_SUCC(membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE, 0, 0));
+
+	TEST_RES(membarrier(MEMBARRIER_CMD_GET_REGISTRATIONS, 0, 0),
+		 _ret == (MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED |
+			  MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE));
+}
+END_TEST()
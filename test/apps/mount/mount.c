@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+
+#include "../network/test.h"
+
+#define BASE_DIR "/tmp/mount_test"
+#define SRC_DIR BASE_DIR "/src"
+#define SRC_FILE SRC_DIR "/file"
+#define BIND_DIR BASE_DIR "/bind"
+#define MOVE_DIR BASE_DIR "/move"
+#define SHARED_DIR BASE_DIR "/shared"
+#define PEER_DIR BASE_DIR "/peer"
+#define SLAVE_DIR BASE_DIR "/slave"
+
+static struct statfs buf;
+
+FN_SETUP(dirs)
+{
+	CHECK(mkdir(BASE_DIR, 0755));
+	CHECK(mkdir(SRC_DIR, 0755));
+	CHECK(mkdir(SRC_DIR "/sub", 0755));
+	CHECK(mkdir(BIND_DIR, 0755));
+	CHECK(mkdir(MOVE_DIR, 0755));
+	CHECK(mkdir(SHARED_DIR, 0755));
+	CHECK(mkdir(SHARED_DIR "/sub", 0755));
+	CHECK(mkdir(SHARED_DIR "/sub2", 0755));
+	CHECK(mkdir(PEER_DIR, 0755));
+	CHECK(mkdir(SLAVE_DIR, 0755));
+	CHECK(close(CHECK(open(SRC_FILE, O_CREAT | O_WRONLY, 0644))));
+}
+END_SETUP()
+
+FN_TEST(bind_mount)
+{
+	TEST_SUCC(mount(SRC_DIR, BIND_DIR, NULL, MS_BIND, NULL));
+	TEST_SUCC(access(BIND_DIR "/file", F_OK));
+
+	TEST_ERRNO(mount(NULL, BIND_DIR "/sub", NULL, MS_REMOUNT | MS_BIND,
+			 NULL),
+		   EINVAL);
+
+	TEST_SUCC(umount(BIND_DIR));
+	TEST_ERRNO(access(BIND_DIR "/file", F_OK), ENOENT);
+}
+END_TEST()
+
+FN_TEST(read_only_bind_mount)
+{
+	TEST_SUCC(mount(SRC_DIR, BIND_DIR, NULL, MS_BIND, NULL));
+	TEST_SUCC(mount(NULL, BIND_DIR, NULL, MS_REMOUNT | MS_BIND | MS_RDONLY,
+			NULL));
+	TEST_RES(statfs(BIND_DIR, &buf), buf.f_flags & ST_RDONLY);
+
+	TEST_ERRNO(open(BIND_DIR "/file", O_WRONLY), EROFS);
+	TEST_ERRNO(open(BIND_DIR "/file", O_RDONLY | O_TRUNC), EROFS);
+	TEST_ERRNO(open(BIND_DIR "/new", O_CREAT | O_WRONLY, 0644), EROFS);
+	TEST_ERRNO(mkdir(BIND_DIR "/dir", 0755), EROFS);
+	TEST_ERRNO(unlink(BIND_DIR "/file"), EROFS);
+	TEST_ERRNO(truncate(BIND_DIR "/file", 0), EROFS);
+	TEST_SUCC(close(TEST_SUCC(open(BIND_DIR "/file", O_RDONLY))));
+
+	// The original mount is still writable.
+	TEST_RES(statfs(SRC_DIR, &buf), !(buf.f_flags & ST_RDONLY));
+	TEST_SUCC(close(TEST_SUCC(open(SRC_FILE, O_WRONLY))));
+
+	TEST_SUCC(mount(NULL, BIND_DIR, NULL, MS_REMOUNT | MS_BIND, NULL));
+	TEST_SUCC(close(TEST_SUCC(open(BIND_DIR "/file", O_WRONLY))));
+
+	TEST_SUCC(umount(BIND_DIR));
+}
+END_TEST()
+
+FN_TEST(move_mount)
+{
+	TEST_SUCC(mount(SRC_DIR, BIND_DIR, NULL, MS_BIND, NULL));
+	TEST_SUCC(mount(BIND_DIR, MOVE_DIR, NULL, MS_MOVE, NULL));
+	TEST_ERRNO(access(BIND_DIR "/file", F_OK), ENOENT);
+	TEST_SUCC(access(MOVE_DIR "/file", F_OK));
+	TEST_SUCC(umount(MOVE_DIR));
+}
+END_TEST()
+
+FN_TEST(shared_mount)
+{
+	TEST_SUCC(mount(SHARED_DIR, SHARED_DIR, NULL, MS_BIND, NULL));
+	TEST_SUCC(mount(NULL, SHARED_DIR, NULL, MS_SHARED, NULL));
+	TEST_SUCC(mount(SHARED_DIR, PEER_DIR, NULL, MS_BIND, NULL));
+
+	// The mount events are propagated in both directions between peers.
+	TEST_SUCC(mount(SRC_DIR, SHARED_DIR "/sub", NULL, MS_BIND, NULL));
+	TEST_SUCC(access(PEER_DIR "/sub/file", F_OK));
+	TEST_SUCC(mount(SRC_DIR, PEER_DIR "/sub2", NULL, MS_BIND, NULL));
+	TEST_SUCC(access(SHARED_DIR "/sub2/file", F_OK));
+
+	// A mount under a shared mount cannot be moved.
+	TEST_ERRNO(mount(SHARED_DIR "/sub", BIND_DIR, NULL, MS_MOVE, NULL),
+		   EINVAL);
+
+	TEST_SUCC(umount(SHARED_DIR "/sub"));
+	TEST_ERRNO(access(PEER_DIR "/sub/file", F_OK), ENOENT);
+	TEST_SUCC(umount(SHARED_DIR "/sub2"));
+	TEST_ERRNO(access(PEER_DIR "/sub2/file", F_OK), ENOENT);
+}
+END_TEST()
+
+FN_TEST(slave_mount)
+{
+	TEST_SUCC(mount(SHARED_DIR, SLAVE_DIR, NULL, MS_BIND, NULL));
+	TEST_SUCC(mount(NULL, SLAVE_DIR, NULL, MS_SLAVE, NULL));
+
+	// The mount events are propagated from the master to the slave, but not
+	// back.
+	TEST_SUCC(mount(SRC_DIR, SHARED_DIR "/sub", NULL, MS_BIND, NULL));
+	TEST_SUCC(access(SLAVE_DIR "/sub/file", F_OK));
+	TEST_SUCC(access(PEER_DIR "/sub/file", F_OK));
+	TEST_SUCC(mount(SRC_DIR, SLAVE_DIR "/sub2", NULL, MS_BIND, NULL));
+	TEST_ERRNO(access(SHARED_DIR "/sub2/file", F_OK), ENOENT);
+
+	TEST_SUCC(umount(SLAVE_DIR "/sub2"));
+	TEST_SUCC(umount(SHARED_DIR "/sub"));
+	TEST_ERRNO(access(SLAVE_DIR "/sub/file", F_OK), ENOENT);
+	TEST_SUCC(umount(SLAVE_DIR));
+}
+END_TEST()
+
+FN_TEST(private_mount)
+{
+	TEST_SUCC(mount(NULL, PEER_DIR, NULL, MS_PRIVATE, NULL));
+
+	TEST_SUCC(mount(SRC_DIR, SHARED_DIR "/sub", NULL, MS_BIND, NULL));
+	TEST_ERRNO(access(PEER_DIR "/sub/file", F_OK), ENOENT);
+	TEST_SUCC(umount(SHARED_DIR "/sub"));
+
+	TEST_ERRNO(mount(NULL, PEER_DIR, NULL, MS_PRIVATE | MS_SHARED, NULL),
+		   EINVAL);
+	TEST_ERRNO(mount(NULL, PEER_DIR "/sub", NULL, MS_PRIVATE, NULL),
+		   EINVAL);
+
+	TEST_SUCC(umount(PEER_DIR));
+}
+END_TEST()
+
+FN_TEST(unbindable_mount)
+{
+	TEST_SUCC(mount(SRC_DIR, SHARED_DIR "/sub", NULL, MS_BIND, NULL));
+	TEST_SUCC(mount(NULL, SHARED_DIR, NULL, MS_UNBINDABLE, NULL));
+	TEST_ERRNO(mount(SHARED_DIR, BIND_DIR, NULL, MS_BIND, NULL), EINVAL);
+
+	// A recursive bind mount skips the unbindable mounts and the mounts
+	// under them.
+	TEST_SUCC(mount(BASE_DIR, BIND_DIR, NULL, MS_BIND | MS_REC, NULL));
+	TEST_SUCC(access(BIND_DIR "/src/file", F_OK));
+	TEST_ERRNO(access(BIND_DIR "/shared/sub/file", F_OK), ENOENT);
+	TEST_SUCC(umount(BIND_DIR));
+
+	TEST_SUCC(umount(SHARED_DIR "/sub"));
+	TEST_SUCC(umount(SHARED_DIR));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(unlink(SRC_FILE));
+	CHECK(rmdir(SRC_DIR "/sub"));
+	CHECK(rmdir(SRC_DIR));
+	CHECK(rmdir(BIND_DIR));
+	CHECK(rmdir(MOVE_DIR));
+	CHECK(rmdir(SHARED_DIR "/sub"));
+	CHECK(rmdir(SHARED_DIR "/sub2"));
+	CHECK(rmdir(SHARED_DIR));
+	CHECK(rmdir(PEER_DIR));
+	CHECK(rmdir(SLAVE_DIR));
+	CHECK(rmdir(BASE_DIR));
+}
+END_SETUP()
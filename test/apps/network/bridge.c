@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <linux/if_link.h>
+#include <linux/rtnetlink.h>
+#include <net/if.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define ETHER_NAME "eth0"
+#define LOOPBACK_NAME "lo"
+#define BRIDGE_NAME "br0"
+
+#define BUFFER_SIZE 8192
+
+static int rtnl_sk;
+
+FN_SETUP(rtnl_sk)
+{
+	rtnl_sk = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+}
+END_SETUP()
+
+struct rtnl_req {
+	struct nlmsghdr hdr;
+	struct ifinfomsg ifi;
+	char attrs[256];
+};
+
+static struct rtattr *add_attr(struct rtnl_req *req, unsigned short type,
+			       const void *data, size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	if (len != 0)
+		memcpy(RTA_DATA(rta), data, len);
+
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + RTA_ALIGN(rta->rta_len);
+
+	return rta;
+}
+
+static struct rtattr *begin_nested(struct rtnl_req *req, unsigned short type)
+{
+	return add_attr(req, type, NULL, 0);
+}
+
+static void end_nested(struct rtnl_req *req, struct rtattr *nested)
+{
+	nested->rta_len = (char *)req + req->hdr.nlmsg_len - (char *)nested;
+}
+
+static void init_link_req(struct rtnl_req *req, int type, int flags,
+			  const char *name)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifinfomsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->ifi.ifi_family = AF_UNSPEC;
+
+	if (name != NULL)
+		add_attr(req, IFLA_IFNAME, name, strlen(name) + 1);
+}
+
+static void add_link_info(struct rtnl_req *req, const char *kind,
+			  int ageing_time, int stp_state)
+{
+	struct rtattr *link_info, *data;
+
+	link_info = begin_nested(req, IFLA_LINKINFO);
+	add_attr(req, IFLA_INFO_KIND, kind, strlen(kind));
+	if (ageing_time >= 0 || stp_state >= 0) {
+		data = begin_nested(req, IFLA_INFO_DATA);
+		if (ageing_time >= 0)
+			add_attr(req, IFLA_BR_AGEING_TIME, &ageing_time,
+				 sizeof(ageing_time));
+		if (stp_state >= 0)
+			add_attr(req, IFLA_BR_STP_STATE, &stp_state,
+				 sizeof(stp_state));
+		end_nested(req, data);
+	}
+	end_nested(req, link_info);
+}
+
+// Sends the request and returns the error code in the acknowledgment.
+static int rtnl_ack(struct rtnl_req *req)
+{
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+	if (send(rtnl_sk, req, req->hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != NLMSG_ERROR)
+		return -1;
+
+	return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+}
+
+static int set_master(const char *name, int master)
+{
+	struct rtnl_req req;
+
+	init_link_req(&req, RTM_SETLINK, 0, name);
+	add_attr(&req, IFLA_MASTER, &master, sizeof(master));
+
+	return rtnl_ack(&req);
+}
+
+static int del_link(const char *name)
+{
+	struct rtnl_req req;
+
+	init_link_req(&req, RTM_DELLINK, 0, name);
+
+	return rtnl_ack(&req);
+}
+
+// Returns the ageing time of the bridge, or -1 if the link is not a bridge.
+static int get_ageing_time(const char *name)
+{
+	struct rtnl_req req;
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct ifinfomsg *ifi = NLMSG_DATA(nlh);
+	struct rtattr *rta, *info_rta, *data_rta;
+	int rta_len, info_len, data_len;
+	int ageing_time = -1;
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifinfomsg));
+	req.hdr.nlmsg_type = RTM_GETLINK;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST;
+	req.ifi.ifi_family = AF_UNSPEC;
+	req.ifi.ifi_index = if_nametoindex(name);
+
+	if (send(rtnl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != RTM_NEWLINK)
+		return -1;
+
+	rta = IFLA_RTA(ifi);
+	rta_len = IFLA_PAYLOAD(nlh);
+	for (; RTA_OK(rta, rta_len); rta = RTA_NEXT(rta, rta_len)) {
+		if (rta->rta_type != IFLA_LINKINFO)
+			continue;
+
+		info_rta = RTA_DATA(rta);
+		info_len = RTA_PAYLOAD(rta);
+		for (; RTA_OK(info_rta, info_len);
+		     info_rta = RTA_NEXT(info_rta, info_len)) {
+			if (info_rta->rta_type != IFLA_INFO_DATA)
+				continue;
+
+			data_rta = RTA_DATA(info_rta);
+			data_len = RTA_PAYLOAD(info_rta);
+			for (; RTA_OK(data_rta, data_len);
+			     data_rta = RTA_NEXT(data_rta, data_len)) {
+				if (data_rta->rta_type == IFLA_BR_AGEING_TIME)
+					memcpy(&ageing_time, RTA_DATA(data_rta),
+					       sizeof(ageing_time));
+			}
+		}
+	}
+
+	return ageing_time;
+}
+
+FN_TEST(new_bridge)
+{
+	struct rtnl_req req;
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL,
+		      BRIDGE_NAME);
+	add_link_info(&req, "bridge", 1000, -1);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -EEXIST);
+
+	TEST_RES(if_nametoindex(BRIDGE_NAME), _ret > 0);
+	TEST_RES(get_ageing_time(BRIDGE_NAME), _ret == 1000);
+	TEST_RES(get_ageing_time(ETHER_NAME), _ret == -1);
+}
+END_TEST()
+
+FN_TEST(change_bridge)
+{
+	struct rtnl_req req;
+
+	init_link_req(&req, RTM_NEWLINK, 0, BRIDGE_NAME);
+	add_link_info(&req, "bridge", 2000, -1);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(get_ageing_time(BRIDGE_NAME), _ret == 2000);
+
+	init_link_req(&req, RTM_NEWLINK, 0, BRIDGE_NAME);
+	add_link_info(&req, "bridge", 3000, 1);
+	TEST_RES(rtnl_ack(&req), _ret == -EOPNOTSUPP);
+	TEST_RES(get_ageing_time(BRIDGE_NAME), _ret == 2000);
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_REPLACE, BRIDGE_NAME);
+	add_link_info(&req, "bridge", -1, -1);
+	TEST_RES(rtnl_ack(&req), _ret == -EOPNOTSUPP);
+
+	init_link_req(&req, RTM_NEWLINK, 0, ETHER_NAME);
+	add_link_info(&req, "bridge", 2000, -1);
+	TEST_RES(rtnl_ack(&req), _ret == -EOPNOTSUPP);
+}
+END_TEST()
+
+FN_TEST(new_link_error)
+{
+	struct rtnl_req req;
+
+	init_link_req(&req, RTM_NEWLINK, 0, "br1");
+	add_link_info(&req, "bridge", -1, -1);
+	TEST_RES(rtnl_ack(&req), _ret == -ENODEV);
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_CREATE, "br1");
+	TEST_RES(rtnl_ack(&req), _ret == -EOPNOTSUPP);
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_CREATE, "br1");
+	add_link_info(&req, "dummy", -1, -1);
+	TEST_RES(rtnl_ack(&req), _ret == -EOPNOTSUPP);
+
+	TEST_RES(if_nametoindex("br1"), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(set_master)
+{
+	struct rtnl_req req;
+	int bridge = TEST_SUCC(if_nametoindex(BRIDGE_NAME));
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, NULL);
+	add_link_info(&req, "bridge", -1, -1);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(if_nametoindex("bridge0"), _ret > 0);
+
+	TEST_RES(set_master("bridge0", bridge), _ret == -ELOOP);
+	TEST_RES(set_master(LOOPBACK_NAME, bridge), _ret == -EINVAL);
+	TEST_RES(set_master(LOOPBACK_NAME, if_nametoindex(ETHER_NAME)),
+		 _ret == -EOPNOTSUPP);
+	TEST_RES(set_master(LOOPBACK_NAME, 9999), _ret == -EINVAL);
+	TEST_RES(set_master(LOOPBACK_NAME, 0), _ret == 0);
+	TEST_RES(set_master("br1", bridge), _ret == -ENODEV);
+}
+END_TEST()
+
+FN_TEST(del_bridge)
+{
+	TEST_RES(del_link("bridge0"), _ret == 0);
+	TEST_RES(del_link(BRIDGE_NAME), _ret == 0);
+	TEST_RES(del_link(BRIDGE_NAME), _ret == -ENODEV);
+	TEST_RES(del_link(ETHER_NAME), _ret == -EOPNOTSUPP);
+
+	TEST_RES(if_nametoindex(BRIDGE_NAME), _ret == 0);
+	TEST_RES(if_nametoindex("bridge0"), _ret == 0);
+}
+END_TEST()
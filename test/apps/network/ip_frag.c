@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <linux/if_tun.h>
+#include <linux/rtnetlink.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define TUN_PATH "/dev/net/tun"
+#define TUN_NAME "tunfrag"
+
+#define TUN_ADDR "10.0.7.1"
+#define PEER_ADDR "10.0.7.2"
+
+#define LOCAL_PORT 5678
+#define PEER_PORT 1234
+
+#define IP_HLEN 20
+#define UDP_HLEN 8
+
+// The MTU of TUN devices is 1500, so each fragment carries at most 1480 bytes.
+#define FRAG_LEN 1480
+#define MSG_LEN 3000
+#define DGRAM_LEN (UDP_HLEN + MSG_LEN)
+
+#define IP_MF 0x2000
+#define IP_DF 0x4000
+#define IP_OFFMASK 0x1fff
+
+static int tun_fd;
+static int udp_sk;
+
+static unsigned char dgram[DGRAM_LEN];
+
+static int open_tun(const char *name)
+{
+	struct ifreq ifr;
+	int fd;
+
+	fd = open(TUN_PATH, O_RDWR);
+	if (fd < 0)
+		return -1;
+
+	memset(&ifr, 0, sizeof(ifr));
+	strncpy(ifr.ifr_name, name, IFNAMSIZ - 1);
+	ifr.ifr_flags = IFF_TUN | IFF_NO_PI;
+	if (ioctl(fd, TUNSETIFF, &ifr) < 0 ||
+	    fcntl(fd, F_SETFL, O_NONBLOCK) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	return fd;
+}
+
+// Adds the IPv4 address to the interface with rtnetlink.
+static int add_addr(const char *name, const char *addr, int prefix_len)
+{
+	struct {
+		struct nlmsghdr hdr;
+		struct ifaddrmsg ifa;
+		char attrs[64];
+	} req;
+	struct rtattr *rta;
+	char buffer[4096];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	int sk, err;
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifaddrmsg));
+	req.hdr.nlmsg_type = RTM_NEWADDR;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE |
+			      NLM_F_EXCL;
+	req.ifa.ifa_family = AF_INET;
+	req.ifa.ifa_prefixlen = prefix_len;
+	req.ifa.ifa_index = if_nametoindex(name);
+
+	rta = (struct rtattr *)((char *)&req + NLMSG_ALIGN(req.hdr.nlmsg_len));
+	rta->rta_type = IFA_LOCAL;
+	rta->rta_len = RTA_LENGTH(sizeof(struct in_addr));
+	inet_pton(AF_INET, addr, RTA_DATA(rta));
+	req.hdr.nlmsg_len = NLMSG_ALIGN(req.hdr.nlmsg_len) + rta->rta_len;
+
+	sk = socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE);
+	if (sk < 0)
+		return -1;
+	if (send(sk, &req, req.hdr.nlmsg_len, 0) < 0 ||
+	    recv(sk, buffer, sizeof(buffer), 0) < 0 ||
+	    nlh->nlmsg_type != NLMSG_ERROR) {
+		close(sk);
+		return -1;
+	}
+	close(sk);
+
+	err = ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+	if (err < 0) {
+		errno = -err;
+		return -1;
+	}
+
+	return 0;
+}
+
+static unsigned short ip_checksum(const unsigned char *header)
+{
+	unsigned int sum = 0;
+
+	for (int i = 0; i < IP_HLEN; i += 2)
+		sum += (header[i] << 8) | header[i + 1];
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return ~sum;
+}
+
+// Writes a fragment of the UDP datagram from the peer to the file. The
+// fragment carries the bytes in `[offset, offset + len)` of the datagram.
+static int write_frag(unsigned short ident, size_t offset, size_t len)
+{
+	unsigned char packet[IP_HLEN + DGRAM_LEN];
+	size_t total_len = IP_HLEN + len;
+	unsigned short frag = offset / 8;
+	unsigned short csum;
+
+	if (offset + len < DGRAM_LEN)
+		frag |= IP_MF;
+
+	memset(packet, 0, IP_HLEN);
+	packet[0] = 0x45;
+	packet[2] = total_len >> 8;
+	packet[3] = total_len & 0xff;
+	packet[4] = ident >> 8;
+	packet[5] = ident & 0xff;
+	packet[6] = frag >> 8;
+	packet[7] = frag & 0xff;
+	packet[8] = 64;
+	packet[9] = IPPROTO_UDP;
+	inet_pton(AF_INET, PEER_ADDR, &packet[12]);
+	inet_pton(AF_INET, TUN_ADDR, &packet[16]);
+	csum = ip_checksum(packet);
+	packet[10] = csum >> 8;
+	packet[11] = csum & 0xff;
+
+	memcpy(&packet[IP_HLEN], &dgram[offset], len);
+
+	return write(tun_fd, packet, total_len) == total_len ? 0 : -1;
+}
+
+// Checks that the packet is a fragment of the UDP datagram from the local port
+// to the peer, which carries the bytes in `[offset, offset + frag_len)`.
+static int check_frag(const unsigned char *packet, size_t len,
+		      unsigned short ident, size_t offset, size_t frag_len)
+{
+	unsigned short frag = (packet[6] << 8) | packet[7];
+	const unsigned char *payload = &packet[IP_HLEN];
+
+	if (len != IP_HLEN + frag_len || packet[0] != 0x45 ||
+	    packet[9] != IPPROTO_UDP || ip_checksum(packet) != 0)
+		return 0;
+
+	if (((packet[4] << 8) | packet[5]) != ident || (frag & IP_DF) ||
+	    !!(frag & IP_MF) != (offset + frag_len < DGRAM_LEN) ||
+	    (frag & IP_OFFMASK) * 8 != offset)
+		return 0;
+
+	// Only the first fragment carries the UDP header, whose ports are the
+	// reverse of those in `dgram`.
+	if (offset == 0) {
+		if (memcmp(&payload[0], &dgram[2], 2) != 0 ||
+		    memcmp(&payload[2], &dgram[0], 2) != 0 ||
+		    memcmp(&payload[4], &dgram[4], 2) != 0)
+			return 0;
+		payload += UDP_HLEN;
+		offset += UDP_HLEN;
+		frag_len -= UDP_HLEN;
+	}
+
+	return memcmp(payload, &dgram[offset], frag_len) == 0;
+}
+
+FN_SETUP(init)
+{
+	struct sockaddr_in sin;
+
+	tun_fd = CHECK(open_tun(TUN_NAME));
+	CHECK(add_addr(TUN_NAME, TUN_ADDR, 24));
+
+	udp_sk = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	sin.sin_family = AF_INET;
+	sin.sin_port = htons(LOCAL_PORT);
+	inet_pton(AF_INET, TUN_ADDR, &sin.sin_addr);
+	CHECK(bind(udp_sk, (struct sockaddr *)&sin, sizeof(sin)));
+
+	// The UDP header of the datagram from the peer. The checksum is zero,
+	// so it is not verified.
+	memset(dgram, 0, UDP_HLEN);
+	dgram[0] = PEER_PORT >> 8;
+	dgram[1] = PEER_PORT & 0xff;
+	dgram[2] = LOCAL_PORT >> 8;
+	dgram[3] = LOCAL_PORT & 0xff;
+	dgram[4] = DGRAM_LEN >> 8;
+	dgram[5] = DGRAM_LEN & 0xff;
+	for (int i = UDP_HLEN; i < DGRAM_LEN; ++i)
+		dgram[i] = i * 7;
+}
+END_SETUP()
+
+FN_TEST(fragment_send)
+{
+	unsigned char packet[2048];
+	struct sockaddr_in sin;
+	unsigned short ident;
+	int len;
+
+	sin.sin_family = AF_INET;
+	sin.sin_port = htons(PEER_PORT);
+	inet_pton(AF_INET, PEER_ADDR, &sin.sin_addr);
+
+	TEST_RES(sendto(udp_sk, &dgram[UDP_HLEN], MSG_LEN, 0,
+			(struct sockaddr *)&sin, sizeof(sin)),
+		 _ret == MSG_LEN);
+
+	// The datagram is fragmented without the "Don't Fragment" flag, and all
+	// fragments share the same identification.
+	len = TEST_SUCC(read(tun_fd, packet, sizeof(packet)));
+	ident = (packet[4] << 8) | packet[5];
+	TEST_RES(check_frag(packet, len, ident, 0, FRAG_LEN), _ret);
+	TEST_RES(read(tun_fd, packet, sizeof(packet)),
+		 check_frag(packet, _ret, ident, FRAG_LEN, FRAG_LEN));
+	TEST_RES(read(tun_fd, packet, sizeof(packet)),
+		 check_frag(packet, _ret, ident, 2 * FRAG_LEN,
+			    DGRAM_LEN - 2 * FRAG_LEN));
+	TEST_ERRNO(read(tun_fd, packet, sizeof(packet)), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(reassemble_out_of_order)
+{
+	char buf[MSG_LEN + 1];
+
+	TEST_SUCC(write_frag(1, 2 * FRAG_LEN, DGRAM_LEN - 2 * FRAG_LEN));
+	TEST_ERRNO(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+	TEST_SUCC(write_frag(1, 0, FRAG_LEN));
+	TEST_ERRNO(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+	TEST_SUCC(write_frag(1, FRAG_LEN, FRAG_LEN));
+
+	TEST_RES(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == MSG_LEN &&
+			 memcmp(buf, &dgram[UDP_HLEN], MSG_LEN) == 0);
+}
+END_TEST()
+
+FN_TEST(reassemble_duplicate)
+{
+	char buf[MSG_LEN + 1];
+
+	// Duplicate fragments are ignored.
+	TEST_SUCC(write_frag(2, 0, FRAG_LEN));
+	TEST_SUCC(write_frag(2, 0, FRAG_LEN));
+	TEST_SUCC(write_frag(2, FRAG_LEN, FRAG_LEN));
+	TEST_SUCC(write_frag(2, FRAG_LEN, 8));
+	TEST_SUCC(write_frag(2, 2 * FRAG_LEN, DGRAM_LEN - 2 * FRAG_LEN));
+
+	TEST_RES(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == MSG_LEN &&
+			 memcmp(buf, &dgram[UDP_HLEN], MSG_LEN) == 0);
+	TEST_ERRNO(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(reassemble_overlap)
+{
+	char buf[MSG_LEN + 1];
+
+	// Overlapping fragments discard the whole datagram.
+	TEST_SUCC(write_frag(3, 0, FRAG_LEN));
+	TEST_SUCC(write_frag(3, FRAG_LEN - 8, FRAG_LEN));
+	TEST_SUCC(write_frag(3, FRAG_LEN, FRAG_LEN));
+	TEST_SUCC(write_frag(3, 2 * FRAG_LEN, DGRAM_LEN - 2 * FRAG_LEN));
+	TEST_ERRNO(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	// The fragments received after the overlap start a new datagram.
+	TEST_SUCC(write_frag(3, 0, FRAG_LEN));
+	TEST_RES(recv(udp_sk, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == MSG_LEN &&
+			 memcmp(buf, &dgram[UDP_HLEN], MSG_LEN) == 0);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(udp_sk));
+	CHECK(close(tun_fd));
+}
+END_SETUP()
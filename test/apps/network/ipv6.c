@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <unistd.h>
+#include <sys/signal.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+
+#include "test.h"
+
+static struct sockaddr_in6 sk_addr;
+static struct sockaddr_in6 sk_mapped_addr;
+
+#define S_PORT htons(0x1236)
+#define U_PORT htons(0x1237)
+#define M_PORT htons(0x1238)
+
+FN_SETUP(general)
+{
+	sk_addr.sin6_family = AF_INET6;
+	sk_addr.sin6_addr = in6addr_loopback;
+
+	sk_mapped_addr.sin6_family = AF_INET6;
+	CHECK(inet_pton(AF_INET6, "::ffff:127.0.0.1",
+			&sk_mapped_addr.sin6_addr));
+
+	signal(SIGPIPE, SIG_IGN);
+}
+END_SETUP()
+
+static int sk_unbound;
+static int sk_listen;
+static int sk_connected;
+static int sk_accepted;
+static int sk_udp;
+static int sk_udp_peer;
+
+FN_SETUP(unbound)
+{
+	sk_unbound = CHECK(socket(PF_INET6, SOCK_STREAM, 0));
+}
+END_SETUP()
+
+FN_SETUP(listen)
+{
+	sk_listen = CHECK(socket(PF_INET6, SOCK_STREAM, 0));
+
+	sk_addr.sin6_port = S_PORT;
+	CHECK(bind(sk_listen, (struct sockaddr *)&sk_addr, sizeof(sk_addr)));
+
+	CHECK(listen(sk_listen, 2));
+}
+END_SETUP()
+
+FN_SETUP(connected)
+{
+	sk_connected = CHECK(socket(PF_INET6, SOCK_STREAM, 0));
+
+	sk_addr.sin6_port = S_PORT;
+	CHECK(connect(sk_connected, (struct sockaddr *)&sk_addr,
+		      sizeof(sk_addr)));
+}
+END_SETUP()
+
+FN_SETUP(accepted)
+{
+	sk_accepted = CHECK(accept(sk_listen, NULL, NULL));
+}
+END_SETUP()
+
+FN_SETUP(udp)
+{
+	sk_udp = CHECK(socket(PF_INET6, SOCK_DGRAM, 0));
+
+	sk_addr.sin6_port = U_PORT;
+	CHECK(bind(sk_udp, (struct sockaddr *)&sk_addr, sizeof(sk_addr)));
+
+	sk_udp_peer = CHECK(socket(PF_INET6, SOCK_DGRAM, 0));
+}
+END_SETUP()
+
+FN_TEST(getsockname)
+{
+	struct sockaddr_in6 saddr;
+	struct sockaddr *psaddr = (struct sockaddr *)&saddr;
+	socklen_t addrlen = sizeof(saddr);
+
+	TEST_RES(getsockname(sk_unbound, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port == 0 &&
+			 IN6_IS_ADDR_UNSPECIFIED(&saddr.sin6_addr));
+
+	TEST_RES(getsockname(sk_listen, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port == S_PORT &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+
+	TEST_RES(getsockname(sk_connected, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port != S_PORT &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+
+	TEST_RES(getsockname(sk_udp, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port == U_PORT &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+}
+END_TEST()
+
+FN_TEST(getpeername)
+{
+	struct sockaddr_in6 saddr;
+	struct sockaddr *psaddr = (struct sockaddr *)&saddr;
+	socklen_t addrlen = sizeof(saddr);
+
+	TEST_RES(getpeername(sk_connected, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port == S_PORT &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+
+	TEST_RES(getpeername(sk_accepted, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port != S_PORT &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+}
+END_TEST()
+
+FN_TEST(tcp_send_recv)
+{
+	char buf[6];
+
+	TEST_RES(send(sk_connected, "hello", 6, 0), _ret == 6);
+
+	TEST_RES(recv(sk_accepted, buf, sizeof(buf), 0),
+		 _ret == 6 && strcmp(buf, "hello") == 0);
+}
+END_TEST()
+
+FN_TEST(udp_send_recv)
+{
+	struct sockaddr_in6 saddr;
+	struct sockaddr *psaddr = (struct sockaddr *)&saddr;
+	socklen_t addrlen = sizeof(saddr);
+	char buf[6];
+
+	sk_addr.sin6_port = U_PORT;
+	TEST_RES(sendto(sk_udp_peer, "hello", 6, 0, (struct sockaddr *)&sk_addr,
+			sizeof(sk_addr)),
+		 _ret == 6);
+
+	TEST_RES(recvfrom(sk_udp, buf, sizeof(buf), 0, psaddr, &addrlen),
+		 _ret == 6 && strcmp(buf, "hello") == 0 &&
+			 addrlen == sizeof(saddr) &&
+			 saddr.sin6_family == AF_INET6 &&
+			 IN6_IS_ADDR_LOOPBACK(&saddr.sin6_addr));
+}
+END_TEST()
+
+FN_TEST(v6only)
+{
+	int sk;
+	int v6only;
+	socklen_t optlen = sizeof(v6only);
+
+	sk = TEST_SUCC(socket(PF_INET6, SOCK_DGRAM, 0));
+
+	TEST_RES(getsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &v6only, &optlen),
+		 optlen == sizeof(v6only) && v6only == 0);
+
+	v6only = 1;
+	TEST_SUCC(setsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &v6only,
+			     sizeof(v6only)));
+
+	v6only = 0;
+	TEST_RES(getsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &v6only, &optlen),
+		 optlen == sizeof(v6only) && v6only == 1);
+
+	sk_mapped_addr.sin6_port = M_PORT;
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&sk_mapped_addr,
+			sizeof(sk_mapped_addr)),
+		   EINVAL);
+
+	TEST_SUCC(close(sk));
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM, 0));
+
+	TEST_ERRNO(getsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &v6only, &optlen),
+		   ENOPROTOOPT);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(v4mapped)
+{
+	int sk;
+	struct sockaddr_in6 saddr;
+	struct sockaddr *psaddr = (struct sockaddr *)&saddr;
+	socklen_t addrlen = sizeof(saddr);
+
+	sk = TEST_SUCC(socket(PF_INET6, SOCK_DGRAM, 0));
+
+	sk_mapped_addr.sin6_port = M_PORT;
+	TEST_SUCC(bind(sk, (struct sockaddr *)&sk_mapped_addr,
+		       sizeof(sk_mapped_addr)));
+
+	TEST_RES(getsockname(sk, psaddr, &addrlen),
+		 addrlen == sizeof(saddr) && saddr.sin6_family == AF_INET6 &&
+			 saddr.sin6_port == M_PORT &&
+			 IN6_IS_ADDR_V4MAPPED(&saddr.sin6_addr));
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(family_mismatch)
+{
+	int sk;
+	struct sockaddr_in saddr = { .sin_family = AF_INET };
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM, 0));
+
+	sk_addr.sin6_port = M_PORT;
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&sk_addr, sizeof(sk_addr)),
+		   EAFNOSUPPORT);
+
+	TEST_SUCC(close(sk));
+
+	sk = TEST_SUCC(socket(PF_INET6, SOCK_DGRAM, 0));
+
+	saddr.sin_port = M_PORT;
+	saddr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&saddr, sizeof(saddr)), EINVAL);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+#include <arpa/inet.h>
+#include <ifaddrs.h>
 #include <net/if.h>
 #include <netlink/route/addr.h>
 #include <unistd.h>
@@ -223,3 +225,224 @@ FN_TEST(get_addr_error)
 	TEST_SUCC(close(sock_fd));
 }
 END_TEST()
+
+static int rtnl_sk;
+
+FN_SETUP(rtnl_sk)
+{
+	rtnl_sk = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+}
+END_SETUP()
+
+struct rtnl_req {
+	struct nlmsghdr hdr;
+	union {
+		struct ifaddrmsg ifa;
+		struct rtmsg rtm;
+	};
+	char attrs[64];
+};
+
+static void add_attr(struct rtnl_req *req, unsigned short type,
+		     const void *data, size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_addr_attr(struct rtnl_req *req, unsigned short type,
+			  const char *addr)
+{
+	struct in_addr in_addr;
+
+	inet_pton(AF_INET, addr, &in_addr);
+	add_attr(req, type, &in_addr, sizeof(in_addr));
+}
+
+// Sends the request and returns the error code in the acknowledgment.
+static int rtnl_ack(struct rtnl_req *req)
+{
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+	if (send(rtnl_sk, req, req->hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != NLMSG_ERROR)
+		return -1;
+
+	return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+}
+
+static void init_addr_req(struct rtnl_req *req, int type, int flags,
+			  const char *addr, int prefix_len)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifaddrmsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->ifa.ifa_family = AF_INET;
+	req->ifa.ifa_prefixlen = prefix_len;
+	req->ifa.ifa_index = if_nametoindex(LOOPBACK_NAME);
+
+	add_addr_attr(req, IFA_LOCAL, addr);
+	add_addr_attr(req, IFA_ADDRESS, addr);
+}
+
+static void init_route_req(struct rtnl_req *req, int type, int flags,
+			   const char *dst, int dst_len, const char *gateway)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct rtmsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->rtm.rtm_family = AF_INET;
+	req->rtm.rtm_dst_len = dst_len;
+	req->rtm.rtm_table = RT_TABLE_MAIN;
+	req->rtm.rtm_protocol = RTPROT_BOOT;
+	req->rtm.rtm_type = RTN_UNICAST;
+
+	add_addr_attr(req, RTA_DST, dst);
+	add_addr_attr(req, RTA_GATEWAY, gateway);
+}
+
+// Returns the number of interfaces named `name` that have the IPv4 address.
+static int find_addr_by_libc(const char *name, const char *addr)
+{
+	struct ifaddrs *ifaddrs, *ifa;
+	char buf[INET_ADDRSTRLEN];
+	int found = 0;
+
+	if (getifaddrs(&ifaddrs) < 0)
+		return -1;
+
+	for (ifa = ifaddrs; ifa != NULL; ifa = ifa->ifa_next) {
+		if (ifa->ifa_addr == NULL || ifa->ifa_addr->sa_family != AF_INET)
+			continue;
+		inet_ntop(AF_INET,
+			  &((struct sockaddr_in *)ifa->ifa_addr)->sin_addr,
+			  buf, sizeof(buf));
+		if (strcmp(ifa->ifa_name, name) == 0 && strcmp(buf, addr) == 0)
+			found++;
+	}
+
+	freeifaddrs(ifaddrs);
+	return found;
+}
+
+// Returns the number of IPv4 routes that match the destination and the gateway.
+static int find_route(const char *dst, int dst_len, const char *gateway)
+{
+	struct rtnl_req req;
+	char buffer[BUFFER_SIZE];
+	struct in_addr dst_addr = { 0 }, gateway_addr;
+	int found = 0;
+
+	if (dst != NULL)
+		inet_pton(AF_INET, dst, &dst_addr);
+	inet_pton(AF_INET, gateway, &gateway_addr);
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct rtmsg));
+	req.hdr.nlmsg_type = RTM_GETROUTE;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_DUMP;
+	req.rtm.rtm_family = AF_INET;
+
+	if (send(rtnl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		long len = recv(rtnl_sk, buffer, sizeof(buffer), 0);
+		struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+		if (len < 0)
+			return -1;
+
+		for (; NLMSG_OK(nlh, len); nlh = NLMSG_NEXT(nlh, len)) {
+			struct rtmsg *rtm = NLMSG_DATA(nlh);
+			struct rtattr *rta = RTM_RTA(rtm);
+			int rta_len = RTM_PAYLOAD(nlh);
+			struct in_addr route_dst = { 0 }, route_gateway = { 0 };
+
+			if (nlh->nlmsg_type == NLMSG_DONE)
+				return found;
+			if (nlh->nlmsg_type != RTM_NEWROUTE ||
+			    rtm->rtm_family != AF_INET)
+				return -1;
+
+			for (; RTA_OK(rta, rta_len);
+			     rta = RTA_NEXT(rta, rta_len)) {
+				if (rta->rta_type == RTA_DST)
+					memcpy(&route_dst, RTA_DATA(rta), 4);
+				if (rta->rta_type == RTA_GATEWAY)
+					memcpy(&route_gateway, RTA_DATA(rta),
+					       4);
+			}
+
+			if (rtm->rtm_dst_len == dst_len &&
+			    route_dst.s_addr == dst_addr.s_addr &&
+			    route_gateway.s_addr == gateway_addr.s_addr)
+				found++;
+		}
+	}
+}
+
+FN_TEST(getifaddrs)
+{
+	TEST_RES(find_addr_by_libc(LOOPBACK_NAME, "127.0.0.1"), _ret == 1);
+	TEST_RES(find_addr_by_libc(ETHER_NAME, "10.0.2.15"), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(new_del_addr)
+{
+	struct rtnl_req req;
+
+	init_addr_req(&req, RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL,
+		      "10.0.3.15", 24);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -EEXIST);
+	TEST_RES(find_addr_by_libc(LOOPBACK_NAME, "10.0.3.15"), _ret == 1);
+
+	init_addr_req(&req, RTM_DELADDR, 0, "10.0.3.15", 24);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -EADDRNOTAVAIL);
+	TEST_RES(find_addr_by_libc(LOOPBACK_NAME, "10.0.3.15"), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(get_default_route)
+{
+	TEST_RES(find_route(NULL, 0, "10.0.2.2"), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(new_del_route)
+{
+	struct rtnl_req req;
+
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       "192.168.100.0", 24, "10.0.2.3");
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -EEXIST);
+	TEST_RES(find_route("192.168.100.0", 24, "10.0.2.3"), _ret == 1);
+
+	init_route_req(&req, RTM_DELROUTE, 0, "192.168.100.0", 24,
+		       "10.0.2.3");
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -ESRCH);
+	TEST_RES(find_route("192.168.100.0", 24, "10.0.2.3"), _ret == 0);
+
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       "192.168.100.0", 24, "172.16.0.1");
+	TEST_RES(rtnl_ack(&req), _ret == -ENETUNREACH);
+}
+END_TEST()
@@ -0,0 +1,653 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <endian.h>
+#include <linux/netfilter.h>
+#include <linux/netfilter/nf_tables.h>
+#include <linux/netfilter/nfnetlink.h>
+#include <linux/netlink.h>
+#include <netinet/in.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define TABLE_NAME "filter"
+#define CHAIN_NAME "input"
+
+#define UDP_DROP_PORT 8401
+#define UDP_PASS_PORT 8402
+#define TCP_REJECT_PORT 8403
+#define UDP_DNAT_PORT 8404
+#define UDP_DNAT_TO_PORT 8405
+
+#define NAT_TABLE_NAME "nat"
+#define NAT_CHAIN_NAME "output"
+
+#define BUF_SIZE 8192
+
+static int nl_fd;
+static struct sockaddr_nl kernel_addr = { .nl_family = AF_NETLINK };
+
+static char msg_buf[BUF_SIZE];
+static size_t msg_len;
+static unsigned int msg_seq;
+
+static struct nlmsghdr *msg_start(int type, int flags, int family, int res_id)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)(msg_buf + msg_len);
+	struct nfgenmsg *nfg;
+
+	nlh->nlmsg_len = NLMSG_LENGTH(sizeof(struct nfgenmsg));
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = ++msg_seq;
+	nlh->nlmsg_pid = 0;
+
+	nfg = NLMSG_DATA(nlh);
+	nfg->nfgen_family = family;
+	nfg->version = NFNETLINK_V0;
+	nfg->res_id = htons(res_id);
+
+	return nlh;
+}
+
+static struct nlmsghdr *nft_msg_start(int type, int flags)
+{
+	return msg_start((NFNL_SUBSYS_NFTABLES << 8) | type, flags,
+			 NFPROTO_IPV4, 0);
+}
+
+static void msg_end(struct nlmsghdr *nlh)
+{
+	msg_len += NLMSG_ALIGN(nlh->nlmsg_len);
+}
+
+static struct nlattr *attr_put(struct nlmsghdr *nlh, int type,
+			       const void *data, size_t len)
+{
+	struct nlattr *nla = (struct nlattr *)((char *)nlh +
+					       NLMSG_ALIGN(nlh->nlmsg_len));
+
+	nla->nla_type = type;
+	nla->nla_len = NLA_HDRLEN + len;
+	if (len != 0)
+		memcpy((char *)nla + NLA_HDRLEN, data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + NLA_ALIGN(nla->nla_len);
+
+	return nla;
+}
+
+static void attr_put_str(struct nlmsghdr *nlh, int type, const char *str)
+{
+	attr_put(nlh, type, str, strlen(str) + 1);
+}
+
+static void attr_put_be32(struct nlmsghdr *nlh, int type, uint32_t value)
+{
+	uint32_t be_value = htonl(value);
+
+	attr_put(nlh, type, &be_value, sizeof(be_value));
+}
+
+static struct nlattr *nest_start(struct nlmsghdr *nlh, int type)
+{
+	return attr_put(nlh, type | NLA_F_NESTED, NULL, 0);
+}
+
+static void nest_end(struct nlmsghdr *nlh, struct nlattr *nest)
+{
+	nest->nla_len = (char *)nlh + nlh->nlmsg_len - (char *)nest;
+}
+
+static void batch_start(void)
+{
+	msg_len = 0;
+	msg_end(msg_start(NFNL_MSG_BATCH_BEGIN, 0, AF_UNSPEC,
+			  NFNL_SUBSYS_NFTABLES));
+}
+
+static int batch_send(void)
+{
+	msg_end(msg_start(NFNL_MSG_BATCH_END, 0, AF_UNSPEC,
+			  NFNL_SUBSYS_NFTABLES));
+
+	return sendto(nl_fd, msg_buf, msg_len, 0,
+		      (struct sockaddr *)&kernel_addr, sizeof(kernel_addr));
+}
+
+static int single_send(void)
+{
+	return sendto(nl_fd, msg_buf, msg_len, 0,
+		      (struct sockaddr *)&kernel_addr, sizeof(kernel_addr));
+}
+
+// Returns the error code in the acknowledgment, or 1 if there is no acknowledgment.
+static int recv_ack(void)
+{
+	char buf[BUF_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+	int len;
+
+	len = recv(nl_fd, buf, sizeof(buf), MSG_DONTWAIT);
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_type != NLMSG_ERROR)
+		return 1;
+
+	return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+}
+
+static struct nlattr *expr_start(struct nlmsghdr *nlh, const char *name,
+				 struct nlattr **data)
+{
+	struct nlattr *elem = nest_start(nlh, NFTA_LIST_ELEM);
+
+	attr_put_str(nlh, NFTA_EXPR_NAME, name);
+	*data = nest_start(nlh, NFTA_EXPR_DATA);
+
+	return elem;
+}
+
+static void expr_end(struct nlmsghdr *nlh, struct nlattr *elem,
+		     struct nlattr *data)
+{
+	nest_end(nlh, data);
+	nest_end(nlh, elem);
+}
+
+static void put_meta_l4proto(struct nlmsghdr *nlh)
+{
+	struct nlattr *data;
+	struct nlattr *elem = expr_start(nlh, "meta", &data);
+
+	attr_put_be32(nlh, NFTA_META_DREG, NFT_REG_1);
+	attr_put_be32(nlh, NFTA_META_KEY, NFT_META_L4PROTO);
+	expr_end(nlh, elem, data);
+}
+
+static void put_payload_dport(struct nlmsghdr *nlh)
+{
+	struct nlattr *data;
+	struct nlattr *elem = expr_start(nlh, "payload", &data);
+
+	attr_put_be32(nlh, NFTA_PAYLOAD_DREG, NFT_REG_1);
+	attr_put_be32(nlh, NFTA_PAYLOAD_BASE, NFT_PAYLOAD_TRANSPORT_HEADER);
+	attr_put_be32(nlh, NFTA_PAYLOAD_OFFSET, 2);
+	attr_put_be32(nlh, NFTA_PAYLOAD_LEN, 2);
+	expr_end(nlh, elem, data);
+}
+
+static void put_cmp_eq(struct nlmsghdr *nlh, const void *value, size_t len)
+{
+	struct nlattr *data, *cmp_data;
+	struct nlattr *elem = expr_start(nlh, "cmp", &data);
+
+	attr_put_be32(nlh, NFTA_CMP_SREG, NFT_REG_1);
+	attr_put_be32(nlh, NFTA_CMP_OP, NFT_CMP_EQ);
+	cmp_data = nest_start(nlh, NFTA_CMP_DATA);
+	attr_put(nlh, NFTA_DATA_VALUE, value, len);
+	nest_end(nlh, cmp_data);
+	expr_end(nlh, elem, data);
+}
+
+static void put_counter(struct nlmsghdr *nlh)
+{
+	struct nlattr *data;
+	struct nlattr *elem = expr_start(nlh, "counter", &data);
+
+	expr_end(nlh, elem, data);
+}
+
+static void put_verdict(struct nlmsghdr *nlh, int code)
+{
+	struct nlattr *data, *imm_data, *verdict;
+	struct nlattr *elem = expr_start(nlh, "immediate", &data);
+
+	attr_put_be32(nlh, NFTA_IMMEDIATE_DREG, NFT_REG_VERDICT);
+	imm_data = nest_start(nlh, NFTA_IMMEDIATE_DATA);
+	verdict = nest_start(nlh, NFTA_DATA_VERDICT);
+	attr_put_be32(nlh, NFTA_VERDICT_CODE, code);
+	nest_end(nlh, verdict);
+	nest_end(nlh, imm_data);
+	expr_end(nlh, elem, data);
+}
+
+static void put_reject_tcp_rst(struct nlmsghdr *nlh)
+{
+	struct nlattr *data;
+	struct nlattr *elem = expr_start(nlh, "reject", &data);
+
+	attr_put_be32(nlh, NFTA_REJECT_TYPE, NFT_REJECT_TCP_RST);
+	expr_end(nlh, elem, data);
+}
+
+// Adds a rule that matches the destination port of the transport protocol.
+static void put_port_rule(int l4proto, int port, int is_reject)
+{
+	struct nlmsghdr *nlh = nft_msg_start(
+		NFT_MSG_NEWRULE, NLM_F_ACK | NLM_F_CREATE | NLM_F_APPEND);
+	uint8_t proto = l4proto;
+	uint16_t be_port = htons(port);
+	struct nlattr *exprs;
+
+	attr_put_str(nlh, NFTA_RULE_TABLE, TABLE_NAME);
+	attr_put_str(nlh, NFTA_RULE_CHAIN, CHAIN_NAME);
+	exprs = nest_start(nlh, NFTA_RULE_EXPRESSIONS);
+	put_meta_l4proto(nlh);
+	put_cmp_eq(nlh, &proto, sizeof(proto));
+	put_payload_dport(nlh);
+	put_cmp_eq(nlh, &be_port, sizeof(be_port));
+	put_counter(nlh);
+	if (is_reject)
+		put_reject_tcp_rst(nlh);
+	else
+		put_verdict(nlh, NF_DROP);
+	nest_end(nlh, exprs);
+	msg_end(nlh);
+}
+
+static void put_immediate(struct nlmsghdr *nlh, int reg, const void *value,
+			  size_t len)
+{
+	struct nlattr *data, *imm_data;
+	struct nlattr *elem = expr_start(nlh, "immediate", &data);
+
+	attr_put_be32(nlh, NFTA_IMMEDIATE_DREG, reg);
+	imm_data = nest_start(nlh, NFTA_IMMEDIATE_DATA);
+	attr_put(nlh, NFTA_DATA_VALUE, value, len);
+	nest_end(nlh, imm_data);
+	expr_end(nlh, elem, data);
+}
+
+// Adds a rule that redirects UDP packets to another port on the loopback address.
+static void put_dnat_rule(const char *table, const char *chain, int port,
+			  int to_port)
+{
+	struct nlmsghdr *nlh = nft_msg_start(
+		NFT_MSG_NEWRULE, NLM_F_ACK | NLM_F_CREATE | NLM_F_APPEND);
+	uint8_t proto = IPPROTO_UDP;
+	uint16_t be_port = htons(port);
+	uint16_t be_to_port = htons(to_port);
+	uint32_t be_to_addr = htonl(INADDR_LOOPBACK);
+	struct nlattr *exprs, *data, *elem;
+
+	attr_put_str(nlh, NFTA_RULE_TABLE, table);
+	attr_put_str(nlh, NFTA_RULE_CHAIN, chain);
+	exprs = nest_start(nlh, NFTA_RULE_EXPRESSIONS);
+	put_meta_l4proto(nlh);
+	put_cmp_eq(nlh, &proto, sizeof(proto));
+	put_payload_dport(nlh);
+	put_cmp_eq(nlh, &be_port, sizeof(be_port));
+	put_immediate(nlh, NFT_REG_1, &be_to_addr, sizeof(be_to_addr));
+	put_immediate(nlh, NFT_REG_2, &be_to_port, sizeof(be_to_port));
+	elem = expr_start(nlh, "nat", &data);
+	attr_put_be32(nlh, NFTA_NAT_TYPE, NFT_NAT_DNAT);
+	attr_put_be32(nlh, NFTA_NAT_FAMILY, NFPROTO_IPV4);
+	attr_put_be32(nlh, NFTA_NAT_REG_ADDR_MIN, NFT_REG_1);
+	attr_put_be32(nlh, NFTA_NAT_REG_PROTO_MIN, NFT_REG_2);
+	expr_end(nlh, elem, data);
+	nest_end(nlh, exprs);
+	msg_end(nlh);
+}
+
+static void put_table(int type, const char *name)
+{
+	struct nlmsghdr *nlh = nft_msg_start(type, NLM_F_ACK | NLM_F_CREATE);
+
+	attr_put_str(nlh, NFTA_TABLE_NAME, name);
+	msg_end(nlh);
+}
+
+static void put_chain(int type)
+{
+	struct nlmsghdr *nlh = nft_msg_start(type, NLM_F_ACK | NLM_F_CREATE);
+	struct nlattr *hook;
+
+	attr_put_str(nlh, NFTA_CHAIN_TABLE, TABLE_NAME);
+	attr_put_str(nlh, NFTA_CHAIN_NAME, CHAIN_NAME);
+	if (type == NFT_MSG_NEWCHAIN) {
+		hook = nest_start(nlh, NFTA_CHAIN_HOOK);
+		attr_put_be32(nlh, NFTA_HOOK_HOOKNUM, NF_INET_LOCAL_IN);
+		attr_put_be32(nlh, NFTA_HOOK_PRIORITY, 0);
+		nest_end(nlh, hook);
+		attr_put_be32(nlh, NFTA_CHAIN_POLICY, NF_ACCEPT);
+		attr_put_str(nlh, NFTA_CHAIN_TYPE, "filter");
+	}
+	msg_end(nlh);
+}
+
+static struct nlattr *find_attr(void *payload, int len, int type)
+{
+	struct nlattr *nla = payload;
+
+	while (len >= NLA_HDRLEN && nla->nla_len >= NLA_HDRLEN &&
+	       nla->nla_len <= len) {
+		if ((nla->nla_type & NLA_TYPE_MASK) == type)
+			return nla;
+		len -= NLA_ALIGN(nla->nla_len);
+		nla = (struct nlattr *)((char *)nla + NLA_ALIGN(nla->nla_len));
+	}
+
+	return NULL;
+}
+
+#define NLA_DATA(nla) ((void *)((char *)(nla) + NLA_HDRLEN))
+#define NLA_LEN(nla) ((int)(nla)->nla_len - NLA_HDRLEN)
+
+// Returns the packet count of the first counter in a rule segment, or -1 if there is none.
+static long rule_counter_packets(struct nlmsghdr *nlh)
+{
+	int attrs_len = NLMSG_PAYLOAD(nlh, sizeof(struct nfgenmsg));
+	void *attrs = (char *)NLMSG_DATA(nlh) + NLMSG_ALIGN(sizeof(struct nfgenmsg));
+	struct nlattr *exprs, *elem, *name, *data, *packets;
+	int len;
+
+	exprs = find_attr(attrs, attrs_len, NFTA_RULE_EXPRESSIONS);
+	if (exprs == NULL)
+		return -1;
+
+	elem = NLA_DATA(exprs);
+	len = NLA_LEN(exprs);
+	while (len >= NLA_HDRLEN && elem->nla_len >= NLA_HDRLEN &&
+	       elem->nla_len <= len) {
+		name = find_attr(NLA_DATA(elem), NLA_LEN(elem), NFTA_EXPR_NAME);
+		data = find_attr(NLA_DATA(elem), NLA_LEN(elem), NFTA_EXPR_DATA);
+		if (name != NULL && data != NULL &&
+		    strcmp(NLA_DATA(name), "counter") == 0) {
+			packets = find_attr(NLA_DATA(data), NLA_LEN(data),
+					    NFTA_COUNTER_PACKETS);
+			if (packets == NULL)
+				return -1;
+			return be64toh(*(uint64_t *)NLA_DATA(packets));
+		}
+		len -= NLA_ALIGN(elem->nla_len);
+		elem = (struct nlattr *)((char *)elem +
+					 NLA_ALIGN(elem->nla_len));
+	}
+
+	return -1;
+}
+
+// Dumps the rules and returns the packet counts of the counters in them.
+static int dump_counters(long *counters, int max_counters)
+{
+	char buf[BUF_SIZE];
+	struct nlmsghdr *nlh;
+	int num_counters = 0;
+	int len;
+
+	msg_len = 0;
+	msg_end(nft_msg_start(NFT_MSG_GETRULE, NLM_F_DUMP));
+	if (single_send() < 0)
+		return -1;
+
+	for (;;) {
+		len = recv(nl_fd, buf, sizeof(buf), MSG_DONTWAIT);
+		if (len < 0)
+			return -1;
+
+		for (nlh = (struct nlmsghdr *)buf; NLMSG_OK(nlh, len);
+		     nlh = NLMSG_NEXT(nlh, len)) {
+			if (nlh->nlmsg_type == NLMSG_DONE)
+				return num_counters;
+			if (nlh->nlmsg_type !=
+				    ((NFNL_SUBSYS_NFTABLES << 8) |
+				     NFT_MSG_NEWRULE) ||
+			    !(nlh->nlmsg_flags & NLM_F_MULTI) ||
+			    num_counters >= max_counters)
+				return -1;
+			counters[num_counters++] = rule_counter_packets(nlh);
+		}
+	}
+}
+
+static void put_nat_chain(void)
+{
+	struct nlmsghdr *nlh =
+		nft_msg_start(NFT_MSG_NEWCHAIN, NLM_F_ACK | NLM_F_CREATE);
+	struct nlattr *hook;
+
+	attr_put_str(nlh, NFTA_CHAIN_TABLE, NAT_TABLE_NAME);
+	attr_put_str(nlh, NFTA_CHAIN_NAME, NAT_CHAIN_NAME);
+	hook = nest_start(nlh, NFTA_CHAIN_HOOK);
+	attr_put_be32(nlh, NFTA_HOOK_HOOKNUM, NF_INET_LOCAL_OUT);
+	attr_put_be32(nlh, NFTA_HOOK_PRIORITY, -100);
+	nest_end(nlh, hook);
+	attr_put_be32(nlh, NFTA_CHAIN_POLICY, NF_ACCEPT);
+	attr_put_str(nlh, NFTA_CHAIN_TYPE, "nat");
+	msg_end(nlh);
+}
+
+static int udp_drop_fd;
+static int udp_pass_fd;
+static int udp_client_fd;
+static int tcp_listen_fd;
+static int udp_dnat_fd;
+
+static struct sockaddr_in udp_drop_addr;
+static struct sockaddr_in udp_pass_addr;
+static struct sockaddr_in tcp_reject_addr;
+static struct sockaddr_in udp_dnat_addr;
+static struct sockaddr_in udp_dnat_to_addr;
+
+#define C_ADDR(addr) ((struct sockaddr *)&(addr))
+
+static void init_addr(struct sockaddr_in *addr, int port)
+{
+	addr->sin_family = AF_INET;
+	addr->sin_port = htons(port);
+	addr->sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+}
+
+FN_SETUP(sockets)
+{
+	init_addr(&udp_drop_addr, UDP_DROP_PORT);
+	init_addr(&udp_pass_addr, UDP_PASS_PORT);
+	init_addr(&tcp_reject_addr, TCP_REJECT_PORT);
+	init_addr(&udp_dnat_addr, UDP_DNAT_PORT);
+	init_addr(&udp_dnat_to_addr, UDP_DNAT_TO_PORT);
+
+	nl_fd = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_NETFILTER));
+
+	udp_drop_fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(udp_drop_fd, C_ADDR(udp_drop_addr), sizeof(udp_drop_addr)));
+
+	udp_pass_fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(udp_pass_fd, C_ADDR(udp_pass_addr), sizeof(udp_pass_addr)));
+
+	udp_client_fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+
+	tcp_listen_fd = CHECK(socket(AF_INET, SOCK_STREAM, 0));
+	CHECK(bind(tcp_listen_fd, C_ADDR(tcp_reject_addr),
+		   sizeof(tcp_reject_addr)));
+	CHECK(listen(tcp_listen_fd, 2));
+
+	udp_dnat_fd = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(udp_dnat_fd, C_ADDR(udp_dnat_to_addr),
+		   sizeof(udp_dnat_to_addr)));
+}
+END_SETUP()
+
+FN_TEST(modify_outside_batch)
+{
+	msg_len = 0;
+	put_table(NFT_MSG_NEWTABLE, TABLE_NAME);
+	TEST_SUCC(single_send());
+	TEST_RES(recv_ack(), _ret == -EINVAL);
+}
+END_TEST()
+
+FN_TEST(add_rules)
+{
+	batch_start();
+	put_table(NFT_MSG_NEWTABLE, TABLE_NAME);
+	put_chain(NFT_MSG_NEWCHAIN);
+	put_port_rule(IPPROTO_UDP, UDP_DROP_PORT, 0);
+	put_port_rule(IPPROTO_TCP, TCP_REJECT_PORT, 1);
+	TEST_SUCC(batch_send());
+
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(udp_drop)
+{
+	char buf[1] = { 'z' };
+
+	TEST_RES(sendto(udp_client_fd, buf, 1, 0, C_ADDR(udp_drop_addr),
+			sizeof(udp_drop_addr)),
+		 _ret == 1);
+	TEST_RES(sendto(udp_client_fd, buf, 1, 0, C_ADDR(udp_pass_addr),
+			sizeof(udp_pass_addr)),
+		 _ret == 1);
+
+	TEST_RES(recv(udp_pass_fd, buf, 1, 0), _ret == 1);
+	TEST_ERRNO(recv(udp_drop_fd, buf, 1, MSG_DONTWAIT), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(tcp_reject)
+{
+	int sk_connect;
+
+	sk_connect = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_ERRNO(connect(sk_connect, C_ADDR(tcp_reject_addr),
+			   sizeof(tcp_reject_addr)),
+		   ECONNREFUSED);
+	TEST_SUCC(close(sk_connect));
+}
+END_TEST()
+
+FN_TEST(dump_counters)
+{
+	long counters[2];
+
+	TEST_RES(dump_counters(counters, 2),
+		 _ret == 2 && counters[0] == 1 && counters[1] >= 1);
+}
+END_TEST()
+
+FN_TEST(nat_in_filter_chain)
+{
+	batch_start();
+	put_dnat_rule(TABLE_NAME, CHAIN_NAME, UDP_DNAT_PORT, UDP_DNAT_TO_PORT);
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == -EOPNOTSUPP);
+	TEST_RES(recv_ack(), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(udp_dnat)
+{
+	char buf[1] = { 'z' };
+	struct sockaddr_in addr;
+	socklen_t addrlen = sizeof(addr);
+
+	batch_start();
+	put_table(NFT_MSG_NEWTABLE, NAT_TABLE_NAME);
+	put_nat_chain();
+	put_dnat_rule(NAT_TABLE_NAME, NAT_CHAIN_NAME, UDP_DNAT_PORT,
+		      UDP_DNAT_TO_PORT);
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 1);
+
+	// The request is redirected to the new port.
+	TEST_RES(sendto(udp_client_fd, buf, 1, 0, C_ADDR(udp_dnat_addr),
+			sizeof(udp_dnat_addr)),
+		 _ret == 1);
+	TEST_RES(recvfrom(udp_dnat_fd, buf, 1, 0, C_ADDR(addr), &addrlen),
+		 _ret == 1 && addr.sin_addr.s_addr == htonl(INADDR_LOOPBACK));
+
+	// The reply comes from the original port.
+	TEST_RES(sendto(udp_dnat_fd, buf, 1, 0, C_ADDR(addr), addrlen),
+		 _ret == 1);
+	TEST_RES(recvfrom(udp_client_fd, buf, 1, 0, C_ADDR(addr), &addrlen),
+		 _ret == 1 && addr.sin_port == htons(UDP_DNAT_PORT));
+
+	batch_start();
+	put_table(NFT_MSG_DELTABLE, NAT_TABLE_NAME);
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(failed_batch)
+{
+	// Chains with rules cannot be deleted.
+	batch_start();
+	put_chain(NFT_MSG_DELCHAIN);
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == -EBUSY);
+	TEST_RES(recv_ack(), _ret == 1);
+
+	// The new table is discarded because the batch fails.
+	batch_start();
+	put_table(NFT_MSG_NEWTABLE, "tmp");
+	put_table(NFT_MSG_DELTABLE, "nonexistent");
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == 0);
+	TEST_RES(recv_ack(), _ret == -ENOENT);
+	TEST_RES(recv_ack(), _ret == 1);
+
+	msg_len = 0;
+	put_table(NFT_MSG_GETTABLE, "tmp");
+	TEST_SUCC(single_send());
+	TEST_RES(recv_ack(), _ret == -ENOENT);
+}
+END_TEST()
+
+FN_TEST(get_gen)
+{
+	char buf[BUF_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+
+	msg_len = 0;
+	msg_end(msg_start((NFNL_SUBSYS_NFTABLES << 8) | NFT_MSG_GETGEN, 0,
+			  AF_UNSPEC, 0));
+	TEST_SUCC(single_send());
+	TEST_RES(recv(nl_fd, buf, sizeof(buf), MSG_DONTWAIT),
+		 NLMSG_OK(nlh, _ret) &&
+			 nlh->nlmsg_type == ((NFNL_SUBSYS_NFTABLES << 8) |
+					     NFT_MSG_NEWGEN) &&
+			 find_attr((char *)NLMSG_DATA(nlh) +
+					   NLMSG_ALIGN(sizeof(struct nfgenmsg)),
+				   NLMSG_PAYLOAD(nlh, sizeof(struct nfgenmsg)),
+				   NFTA_GEN_ID) != NULL);
+}
+END_TEST()
+
+FN_TEST(delete_table)
+{
+	char buf[1] = { 'z' };
+
+	batch_start();
+	put_table(NFT_MSG_DELTABLE, TABLE_NAME);
+	TEST_SUCC(batch_send());
+	TEST_RES(recv_ack(), _ret == 0);
+
+	TEST_RES(sendto(udp_client_fd, buf, 1, 0, C_ADDR(udp_drop_addr),
+			sizeof(udp_drop_addr)),
+		 _ret == 1);
+	TEST_RES(recv(udp_drop_fd, buf, 1, 0), _ret == 1);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(udp_dnat_fd));
+	CHECK(close(tcp_listen_fd));
+	CHECK(close(udp_client_fd));
+	CHECK(close(udp_pass_fd));
+	CHECK(close(udp_drop_fd));
+	CHECK(close(nl_fd));
+}
+END_SETUP()
@@ -0,0 +1,426 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <arpa/inet.h>
+#include <linux/filter.h>
+#include <linux/if_ether.h>
+#include <linux/if_packet.h>
+#include <net/if.h>
+#include <net/if_arp.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <netinet/udp.h>
+#include <poll.h>
+#include <sys/mman.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define UDP_PORT 0x4321
+#define MESSAGE "packet socket"
+
+static int lo_index;
+static int sk_udp;
+static struct sockaddr_in udp_addr;
+
+FN_SETUP(general)
+{
+	lo_index = CHECK(if_nametoindex("lo"));
+
+	udp_addr.sin_family = AF_INET;
+	udp_addr.sin_port = htons(UDP_PORT);
+	CHECK(inet_aton("127.0.0.1", &udp_addr.sin_addr));
+
+	sk_udp = CHECK(socket(AF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	CHECK(bind(sk_udp, (struct sockaddr *)&udp_addr, sizeof(udp_addr)));
+}
+END_SETUP()
+
+static int new_packet_socket(int type, int protocol)
+{
+	struct sockaddr_ll addr = {
+		.sll_family = AF_PACKET,
+		.sll_protocol = htons(protocol),
+		.sll_ifindex = lo_index,
+	};
+	int sk;
+
+	sk = socket(AF_PACKET, type | SOCK_NONBLOCK, htons(protocol));
+	if (sk < 0)
+		return -1;
+
+	if (bind(sk, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+		close(sk);
+		return -1;
+	}
+
+	return sk;
+}
+
+static int send_udp(void)
+{
+	int sk;
+	int ret;
+
+	sk = socket(AF_INET, SOCK_DGRAM, 0);
+	if (sk < 0)
+		return -1;
+
+	ret = sendto(sk, MESSAGE, sizeof(MESSAGE), 0,
+		     (struct sockaddr *)&udp_addr, sizeof(udp_addr));
+	close(sk);
+
+	return ret;
+}
+
+static int drain_udp(void)
+{
+	char buf[64];
+
+	while (recv(sk_udp, buf, sizeof(buf), 0) >= 0)
+		;
+
+	return errno == EAGAIN ? 0 : -1;
+}
+
+static ssize_t recv_frame(int sk, void *buf, size_t len, int flags,
+			  struct sockaddr_ll *addr)
+{
+	struct pollfd pfd = { .fd = sk, .events = POLLIN };
+	socklen_t addrlen = sizeof(*addr);
+
+	if (poll(&pfd, 1, 1000) != 1) {
+		errno = ETIMEDOUT;
+		return -1;
+	}
+
+	return recvfrom(sk, buf, len, flags, (struct sockaddr *)addr,
+			&addrlen);
+}
+
+static int is_udp_message(const unsigned char *ip, size_t len)
+{
+	const struct iphdr *iph = (const struct iphdr *)ip;
+	size_t ihl;
+
+	if (len < sizeof(struct iphdr) || iph->version != 4 ||
+	    iph->protocol != IPPROTO_UDP)
+		return 0;
+
+	ihl = iph->ihl * 4;
+	if (len != ihl + sizeof(struct udphdr) + sizeof(MESSAGE))
+		return 0;
+
+	return memcmp(ip + ihl + sizeof(struct udphdr), MESSAGE,
+		      sizeof(MESSAGE)) == 0;
+}
+
+FN_TEST(invalid_socket)
+{
+	TEST_ERRNO(socket(AF_PACKET, SOCK_STREAM, htons(ETH_P_ALL)),
+		   ESOCKTNOSUPPORT);
+}
+END_TEST()
+
+FN_TEST(bind_and_getsockname)
+{
+	struct sockaddr_ll addr = {
+		.sll_family = AF_PACKET,
+		.sll_protocol = htons(ETH_P_IP),
+	};
+	socklen_t addrlen = sizeof(addr);
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, 0));
+
+	addr.sll_ifindex = 0x7fffffff;
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&addr, sizeof(addr)), ENODEV);
+
+	addr.sll_ifindex = lo_index;
+	TEST_SUCC(bind(sk, (struct sockaddr *)&addr, sizeof(addr)));
+
+	memset(&addr, 0, sizeof(addr));
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sll_family == AF_PACKET &&
+			 addr.sll_protocol == htons(ETH_P_IP) &&
+			 addr.sll_ifindex == lo_index &&
+			 addr.sll_hatype == ARPHRD_LOOPBACK);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(recv_dgram)
+{
+	unsigned char buf[256];
+	struct sockaddr_ll addr;
+	int sk;
+
+	sk = TEST_SUCC(new_packet_socket(SOCK_DGRAM, ETH_P_IP));
+
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_RES(recv_frame(sk, buf, sizeof(buf), 0, &addr),
+		 is_udp_message(buf, _ret) && addr.sll_ifindex == lo_index &&
+			 addr.sll_protocol == htons(ETH_P_IP) &&
+			 addr.sll_pkttype == PACKET_HOST);
+
+	// Only the sockets receiving all protocols see the outgoing frames.
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+
+	TEST_SUCC(drain_udp());
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(recv_raw)
+{
+	unsigned char buf[256];
+	struct sockaddr_ll addr;
+	int sk;
+
+	sk = TEST_SUCC(new_packet_socket(SOCK_RAW, ETH_P_ALL));
+
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_RES(recv_frame(sk, buf, sizeof(buf), 0, &addr),
+		 _ret > ETH_HLEN && buf[12] == 0x08 && buf[13] == 0x00 &&
+			 is_udp_message(buf + ETH_HLEN, _ret - ETH_HLEN) &&
+			 addr.sll_pkttype == PACKET_OUTGOING);
+	TEST_RES(recv_frame(sk, buf, sizeof(buf), 0, &addr),
+		 _ret > ETH_HLEN &&
+			 is_udp_message(buf + ETH_HLEN, _ret - ETH_HLEN) &&
+			 addr.sll_pkttype == PACKET_HOST);
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+
+	TEST_SUCC(drain_udp());
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+static unsigned short ip_checksum(const void *data, size_t len)
+{
+	const unsigned short *words = data;
+	unsigned int sum = 0;
+
+	for (; len > 1; len -= 2)
+		sum += *words++;
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return ~sum;
+}
+
+FN_TEST(send_dgram)
+{
+	struct {
+		struct iphdr ip;
+		struct udphdr udp;
+		char payload[sizeof(MESSAGE)];
+	} __attribute__((packed)) packet;
+	struct sockaddr_ll addr = {
+		.sll_family = AF_PACKET,
+		.sll_protocol = htons(ETH_P_IP),
+		.sll_ifindex = lo_index,
+	};
+	char buf[64];
+	int sk;
+
+	memset(&packet, 0, sizeof(packet));
+	packet.ip.version = 4;
+	packet.ip.ihl = 5;
+	packet.ip.tot_len = htons(sizeof(packet));
+	packet.ip.ttl = 64;
+	packet.ip.protocol = IPPROTO_UDP;
+	packet.ip.saddr = udp_addr.sin_addr.s_addr;
+	packet.ip.daddr = udp_addr.sin_addr.s_addr;
+	packet.ip.check = ip_checksum(&packet.ip, sizeof(packet.ip));
+	packet.udp.source = htons(UDP_PORT + 1);
+	packet.udp.dest = htons(UDP_PORT);
+	packet.udp.len = htons(sizeof(packet.udp) + sizeof(MESSAGE));
+	memcpy(packet.payload, MESSAGE, sizeof(MESSAGE));
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_DGRAM, 0));
+
+	TEST_ERRNO(send(sk, &packet, sizeof(packet), 0), ENXIO);
+	TEST_RES(sendto(sk, &packet, sizeof(packet), 0,
+			(struct sockaddr *)&addr, sizeof(addr)),
+		 _ret == sizeof(packet));
+	TEST_RES(recv(sk_udp, buf, sizeof(buf), 0),
+		 _ret == sizeof(MESSAGE) && strcmp(buf, MESSAGE) == 0);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(socket_filter)
+{
+	struct sock_filter drop_all[] = {
+		BPF_STMT(BPF_RET | BPF_K, 0),
+	};
+	// Keep the first 20 bytes of IPv4 frames and drop the others.
+	struct sock_filter trim_ipv4[] = {
+		BPF_STMT(BPF_LD | BPF_H | BPF_ABS, 12),
+		BPF_JUMP(BPF_JMP | BPF_JEQ | BPF_K, ETH_P_IP, 0, 1),
+		BPF_STMT(BPF_RET | BPF_K, 20),
+		BPF_STMT(BPF_RET | BPF_K, 0),
+	};
+	struct sock_filter no_ret[] = {
+		BPF_STMT(BPF_LD | BPF_W | BPF_LEN, 0),
+	};
+	struct sock_fprog fprog;
+	unsigned char buf[256];
+	struct sockaddr_ll addr;
+	int sk;
+
+	sk = TEST_SUCC(new_packet_socket(SOCK_RAW, ETH_P_ALL));
+
+	fprog.len = 1;
+	fprog.filter = no_ret;
+	TEST_ERRNO(setsockopt(sk, SOL_SOCKET, SO_ATTACH_FILTER, &fprog,
+			      sizeof(fprog)),
+		   EINVAL);
+	fprog.len = 0;
+	fprog.filter = drop_all;
+	TEST_ERRNO(setsockopt(sk, SOL_SOCKET, SO_ATTACH_FILTER, &fprog,
+			      sizeof(fprog)),
+		   EINVAL);
+
+	fprog.len = 1;
+	TEST_SUCC(setsockopt(sk, SOL_SOCKET, SO_ATTACH_FILTER, &fprog,
+			     sizeof(fprog)));
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+
+	fprog.len = sizeof(trim_ipv4) / sizeof(trim_ipv4[0]);
+	fprog.filter = trim_ipv4;
+	TEST_SUCC(setsockopt(sk, SOL_SOCKET, SO_ATTACH_FILTER, &fprog,
+			     sizeof(fprog)));
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_RES(recv_frame(sk, buf, sizeof(buf), 0, &addr), _ret == 20);
+	TEST_RES(recv_frame(sk, buf, 10, MSG_TRUNC, &addr), _ret == 20);
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+
+	TEST_SUCC(setsockopt(sk, SOL_SOCKET, SO_DETACH_FILTER, NULL, 0));
+	TEST_ERRNO(setsockopt(sk, SOL_SOCKET, SO_DETACH_FILTER, NULL, 0),
+		   ENOENT);
+
+	TEST_SUCC(drain_udp());
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(statistics)
+{
+	struct tpacket_stats stats;
+	socklen_t len = sizeof(stats);
+	unsigned char buf[256];
+	struct sockaddr_ll addr;
+	int sk;
+
+	sk = TEST_SUCC(new_packet_socket(SOCK_DGRAM, ETH_P_IP));
+
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_RES(recv_frame(sk, buf, sizeof(buf), 0, &addr),
+		 is_udp_message(buf, _ret));
+
+	TEST_RES(getsockopt(sk, SOL_PACKET, PACKET_STATISTICS, &stats, &len),
+		 len == sizeof(stats) && stats.tp_packets == 1 &&
+			 stats.tp_drops == 0);
+	TEST_RES(getsockopt(sk, SOL_PACKET, PACKET_STATISTICS, &stats, &len),
+		 len == sizeof(stats) && stats.tp_packets == 0 &&
+			 stats.tp_drops == 0);
+
+	TEST_SUCC(drain_udp());
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+#define BLOCK_SIZE 4096
+#define BLOCK_NR 4
+#define FRAME_SIZE 2048
+
+FN_TEST(rx_ring)
+{
+	struct tpacket_req3 req = {
+		.tp_block_size = BLOCK_SIZE,
+		.tp_block_nr = BLOCK_NR,
+		.tp_frame_size = FRAME_SIZE,
+		.tp_frame_nr = BLOCK_SIZE / FRAME_SIZE * BLOCK_NR,
+		.tp_retire_blk_tov = 10,
+	};
+	struct pollfd pfd = { .events = POLLIN };
+	struct tpacket_block_desc *desc;
+	struct tpacket3_hdr *hdr;
+	int version;
+	socklen_t len = sizeof(version);
+	unsigned char *ring;
+	int sk;
+
+	sk = TEST_SUCC(new_packet_socket(SOCK_RAW, ETH_P_IP));
+	pfd.fd = sk;
+
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			      sizeof(req)),
+		   EINVAL);
+	TEST_ERRNO((long)mmap(NULL, BLOCK_SIZE * BLOCK_NR,
+			      PROT_READ | PROT_WRITE, MAP_SHARED, sk, 0),
+		   EINVAL);
+
+	version = 5;
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_VERSION, &version,
+			      sizeof(version)),
+		   EINVAL);
+	version = TPACKET_V3;
+	TEST_SUCC(setsockopt(sk, SOL_PACKET, PACKET_VERSION, &version,
+			     sizeof(version)));
+	version = 0;
+	TEST_RES(getsockopt(sk, SOL_PACKET, PACKET_VERSION, &version, &len),
+		 len == sizeof(version) && version == TPACKET_V3);
+
+	req.tp_frame_nr++;
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			      sizeof(req)),
+		   EINVAL);
+	req.tp_frame_nr--;
+	TEST_SUCC(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			     sizeof(req)));
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			      sizeof(req)),
+		   EBUSY);
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_VERSION, &version,
+			      sizeof(version)),
+		   EBUSY);
+
+	ring = (unsigned char *)TEST_RES(
+		(long)mmap(NULL, BLOCK_SIZE * BLOCK_NR, PROT_READ | PROT_WRITE,
+			   MAP_SHARED, sk, 0),
+		_ret != (long)MAP_FAILED);
+	desc = (struct tpacket_block_desc *)ring;
+
+	TEST_RES(desc->hdr.bh1.block_status, _ret == TP_STATUS_KERNEL);
+
+	// The block is handed over to the user space when it is retired by
+	// the timer.
+	TEST_RES(send_udp(), _ret == sizeof(MESSAGE));
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1 && (pfd.revents & POLLIN));
+
+	TEST_RES(desc->hdr.bh1.block_status,
+		 (_ret & TP_STATUS_USER) && (_ret & TP_STATUS_BLK_TMO));
+	TEST_RES(desc->hdr.bh1.num_pkts, _ret == 1);
+
+	hdr = (struct tpacket3_hdr *)(ring +
+				      desc->hdr.bh1.offset_to_first_pkt);
+	TEST_RES(hdr->tp_snaplen,
+		 _ret == hdr->tp_len && _ret > ETH_HLEN &&
+			 is_udp_message((unsigned char *)hdr + hdr->tp_net,
+					_ret - ETH_HLEN) &&
+			 hdr->tp_net - hdr->tp_mac == ETH_HLEN);
+
+	desc->hdr.bh1.block_status = TP_STATUS_KERNEL;
+	__sync_synchronize();
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0);
+
+	TEST_SUCC(munmap(ring, BLOCK_SIZE * BLOCK_NR));
+	TEST_SUCC(drain_udp());
+	TEST_SUCC(close(sk));
+}
+END_TEST()
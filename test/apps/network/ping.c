@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <unistd.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <netinet/ip_icmp.h>
+#include <arpa/inet.h>
+#include <linux/errqueue.h>
+
+#include "test.h"
+
+#define PING_GROUP_RANGE "/proc/sys/net/ipv4/ping_group_range"
+
+static int write_ping_group_range(const char *range)
+{
+	int fd, len;
+
+	fd = open(PING_GROUP_RANGE, O_WRONLY);
+	if (fd < 0)
+		return -1;
+
+	len = write(fd, range, strlen(range));
+	close(fd);
+
+	return len;
+}
+
+static int read_ping_group_range(char *buf, size_t len)
+{
+	int fd, ret;
+
+	memset(buf, 0, len);
+
+	fd = open(PING_GROUP_RANGE, O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	ret = read(fd, buf, len - 1);
+	close(fd);
+
+	return ret;
+}
+
+FN_TEST(ping_group_range)
+{
+	char buf[64];
+
+	TEST_RES(write_ping_group_range("1 0"), _ret == 3);
+	TEST_RES(read_ping_group_range(buf, sizeof(buf)),
+		 strcmp(buf, "1\t0\n") == 0);
+	TEST_ERRNO(socket(PF_INET, SOCK_DGRAM, IPPROTO_ICMP), EACCES);
+	TEST_ERRNO(socket(PF_INET6, SOCK_DGRAM, IPPROTO_ICMPV6), EACCES);
+
+	TEST_ERRNO(write_ping_group_range("0 4294967295"), EINVAL);
+
+	// An invalid range is reset to the default empty range
+	TEST_RES(write_ping_group_range("5 3"), _ret == 3);
+	TEST_RES(read_ping_group_range(buf, sizeof(buf)),
+		 strcmp(buf, "1\t0\n") == 0);
+
+	TEST_RES(write_ping_group_range("0 2147483647"), _ret == 12);
+	TEST_RES(read_ping_group_range(buf, sizeof(buf)),
+		 strcmp(buf, "0\t2147483647\n") == 0);
+}
+END_TEST()
+
+static struct sockaddr_in lo_addr;
+
+FN_SETUP(lo_addr)
+{
+	lo_addr.sin_family = AF_INET;
+	CHECK(inet_aton("127.0.0.1", &lo_addr.sin_addr));
+}
+END_SETUP()
+
+static int wait_for_events(int sk, short events)
+{
+	struct pollfd pfd = { .fd = sk, .events = events };
+
+	return poll(&pfd, 1, 1000) == 1 && (pfd.revents & events);
+}
+
+FN_TEST(echo)
+{
+	struct icmphdr request = {
+		.type = ICMP_ECHO,
+		.code = 0,
+		.un.echo.id = htons(0xbeef),
+		.un.echo.sequence = htons(1),
+	};
+	char buf[sizeof(struct icmphdr) + 4];
+	struct icmphdr *reply = (struct icmphdr *)buf;
+	struct sockaddr_in addr, src_addr;
+	socklen_t addrlen = sizeof(addr);
+	int sk;
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK,
+			      IPPROTO_ICMP));
+
+	memcpy(buf, &request, sizeof(request));
+	memcpy(buf + sizeof(request), "ping", 4);
+	TEST_RES(sendto(sk, buf, sizeof(buf), 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == sizeof(buf));
+
+	// The identifier is the port number of the socket
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin_port != 0);
+
+	TEST_RES(wait_for_events(sk, POLLIN), _ret);
+
+	memset(buf, 0, sizeof(buf));
+	addrlen = sizeof(src_addr);
+	TEST_RES(recvfrom(sk, buf, sizeof(buf), 0,
+			  (struct sockaddr *)&src_addr, &addrlen),
+		 _ret == sizeof(buf) && reply->type == ICMP_ECHOREPLY &&
+			 reply->code == 0 &&
+			 reply->un.echo.id == addr.sin_port &&
+			 reply->un.echo.sequence == htons(1) &&
+			 memcmp(buf + sizeof(*reply), "ping", 4) == 0 &&
+			 addrlen == sizeof(src_addr) && src_addr.sin_port == 0 &&
+			 src_addr.sin_addr.s_addr == htonl(INADDR_LOOPBACK));
+
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(invalid_echo)
+{
+	struct icmphdr request = { .type = ICMP_ECHOREPLY, .code = 0 };
+	int sk;
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK,
+			      IPPROTO_ICMP));
+
+	// Only echo requests can be sent
+	TEST_ERRNO(sendto(sk, &request, sizeof(request), 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+
+	request.type = ICMP_ECHO;
+	request.code = 1;
+	TEST_ERRNO(sendto(sk, &request, sizeof(request), 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+
+	// The ICMP header must be complete
+	request.code = 0;
+	TEST_ERRNO(sendto(sk, &request, sizeof(request) - 1, 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+static int get_socket_error(int sk)
+{
+	int err = -1;
+	socklen_t len = sizeof(err);
+
+	if (getsockopt(sk, SOL_SOCKET, SO_ERROR, &err, &len) < 0)
+		return -1;
+
+	return err;
+}
+
+static unsigned short unused_port(void)
+{
+	struct sockaddr_in addr = lo_addr;
+	socklen_t addrlen = sizeof(addr);
+	int sk;
+
+	sk = CHECK(socket(PF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(sk, (struct sockaddr *)&addr, sizeof(addr)));
+	CHECK(getsockname(sk, (struct sockaddr *)&addr, &addrlen));
+	CHECK(close(sk));
+
+	return addr.sin_port;
+}
+
+FN_TEST(udp_port_unreachable)
+{
+	struct sockaddr_in addr = lo_addr;
+	char buf[4];
+	int sk;
+
+	addr.sin_port = unused_port();
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	TEST_SUCC(connect(sk, (struct sockaddr *)&addr, sizeof(addr)));
+
+	TEST_RES(send(sk, "a", 1, 0), _ret == 1);
+	TEST_RES(wait_for_events(sk, POLLERR), _ret);
+
+	// The socket error is reported before any other errors
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), ECONNREFUSED);
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), 0), EAGAIN);
+	TEST_RES(get_socket_error(sk), _ret == 0);
+
+	TEST_RES(send(sk, "b", 1, 0), _ret == 1);
+	TEST_RES(wait_for_events(sk, POLLERR), _ret);
+
+	TEST_RES(get_socket_error(sk), _ret == ECONNREFUSED);
+	TEST_RES(get_socket_error(sk), _ret == 0);
+
+	// The error queue is not used without `IP_RECVERR`
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+static char recv_buf[16];
+static char recv_control[512];
+static struct sockaddr_in recv_addr;
+static struct msghdr recv_msg;
+
+static int recv_error(int sk)
+{
+	static struct iovec iov;
+
+	memset(recv_buf, 0, sizeof(recv_buf));
+	memset(recv_control, 0, sizeof(recv_control));
+	memset(&recv_addr, 0, sizeof(recv_addr));
+
+	iov.iov_base = recv_buf;
+	iov.iov_len = sizeof(recv_buf);
+
+	memset(&recv_msg, 0, sizeof(recv_msg));
+	recv_msg.msg_name = &recv_addr;
+	recv_msg.msg_namelen = sizeof(recv_addr);
+	recv_msg.msg_iov = &iov;
+	recv_msg.msg_iovlen = 1;
+	recv_msg.msg_control = recv_control;
+	recv_msg.msg_controllen = sizeof(recv_control);
+
+	return recvmsg(sk, &recv_msg, MSG_ERRQUEUE);
+}
+
+static struct sock_extended_err *find_extended_err(void)
+{
+	struct cmsghdr *cmsg;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg))
+		if (cmsg->cmsg_level == SOL_IP && cmsg->cmsg_type == IP_RECVERR)
+			return (struct sock_extended_err *)CMSG_DATA(cmsg);
+
+	return NULL;
+}
+
+static int is_lo_offender(struct sock_extended_err *serr)
+{
+	struct sockaddr_in *offender =
+		(struct sockaddr_in *)SO_EE_OFFENDER(serr);
+
+	return offender->sin_family == AF_INET &&
+	       offender->sin_addr.s_addr == htonl(INADDR_LOOPBACK);
+}
+
+FN_TEST(udp_recverr)
+{
+	struct sockaddr_in addr = lo_addr;
+	struct sock_extended_err *serr;
+	int sk, one = 1;
+
+	addr.sin_port = unused_port();
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	TEST_SUCC(setsockopt(sk, SOL_IP, IP_RECVERR, &one, sizeof(one)));
+
+	// The errors are reported even if the socket is not connected
+	TEST_RES(sendto(sk, "abc", 3, 0, (struct sockaddr *)&addr,
+			sizeof(addr)),
+		 _ret == 3);
+	TEST_RES(wait_for_events(sk, POLLERR), _ret);
+
+	TEST_RES(get_socket_error(sk), _ret == ECONNREFUSED);
+
+	TEST_RES(recv_error(sk),
+		 _ret == 3 && memcmp(recv_buf, "abc", 3) == 0 &&
+			 (recv_msg.msg_flags & MSG_ERRQUEUE) &&
+			 recv_addr.sin_port == addr.sin_port &&
+			 recv_addr.sin_addr.s_addr == htonl(INADDR_LOOPBACK) &&
+			 (serr = find_extended_err()) &&
+			 serr->ee_errno == ECONNREFUSED &&
+			 serr->ee_origin == SO_EE_ORIGIN_ICMP &&
+			 serr->ee_type == ICMP_DEST_UNREACH &&
+			 serr->ee_code == ICMP_PORT_UNREACH &&
+			 is_lo_offender(serr));
+	TEST_ERRNO(recv_error(sk), EAGAIN);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(ping_group_range_reset)
+{
+	TEST_RES(write_ping_group_range("1 0"), _ret == 3);
+}
+END_TEST()
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <linux/cn_proc.h>
+#include <linux/connector.h>
+#include <linux/netlink.h>
+#include <signal.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "test.h"
+
+struct proc_msg {
+	struct nlmsghdr nlh;
+	struct cn_msg cn;
+	union {
+		enum proc_cn_mcast_op op;
+		struct proc_event event;
+	};
+} __attribute__((packed));
+
+static int sk_proc;
+static struct proc_msg msg;
+
+static int send_op(enum proc_cn_mcast_op op)
+{
+	memset(&msg, 0, sizeof(msg));
+	msg.nlh.nlmsg_len = NLMSG_LENGTH(sizeof(msg.cn) + sizeof(op));
+	msg.nlh.nlmsg_type = NLMSG_DONE;
+	msg.cn.id.idx = CN_IDX_PROC;
+	msg.cn.id.val = CN_VAL_PROC;
+	msg.cn.seq = 42;
+	msg.cn.ack = 0;
+	msg.cn.len = sizeof(op);
+	msg.op = op;
+
+	return send(sk_proc, &msg, msg.nlh.nlmsg_len, 0);
+}
+
+// Receives the next event of the process, skipping the events of others.
+static int recv_event(pid_t pid, enum what what)
+{
+	for (;;) {
+		if (recv(sk_proc, &msg, sizeof(msg), 0) < 0)
+			return -1;
+		if (msg.event.what != what)
+			continue;
+
+		switch (what) {
+		case PROC_EVENT_FORK:
+			if (msg.event.event_data.fork.child_pid == pid)
+				return 0;
+			break;
+		case PROC_EVENT_EXIT:
+			if (msg.event.event_data.exit.process_pid == pid)
+				return 0;
+			break;
+		default:
+			return 0;
+		}
+	}
+}
+
+FN_SETUP(bind)
+{
+	struct sockaddr_nl addr = {
+		.nl_family = AF_NETLINK,
+		.nl_pid = getpid(),
+		.nl_groups = CN_IDX_PROC,
+	};
+
+	sk_proc = CHECK(socket(PF_NETLINK, SOCK_DGRAM, NETLINK_CONNECTOR));
+	CHECK(bind(sk_proc, (struct sockaddr *)&addr, sizeof(addr)));
+}
+END_SETUP()
+
+FN_TEST(listen)
+{
+	TEST_RES(send_op(PROC_CN_MCAST_LISTEN),
+		 _ret == NLMSG_LENGTH(sizeof(msg.cn) + sizeof(msg.op)));
+
+	TEST_RES(recv_event(0, PROC_EVENT_NONE),
+		 msg.nlh.nlmsg_len == NLMSG_LENGTH(sizeof(msg.cn) +
+						   sizeof(msg.event)) &&
+			 msg.cn.id.idx == CN_IDX_PROC && msg.cn.seq == 42 &&
+			 msg.cn.ack == 1 &&
+			 msg.cn.len == sizeof(struct proc_event) &&
+			 msg.event.event_data.ack.err == 0);
+}
+END_TEST()
+
+FN_TEST(fork_and_exit)
+{
+	unsigned int fork_seq;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(7);
+
+	TEST_RES(recv_event(pid, PROC_EVENT_FORK),
+		 msg.event.event_data.fork.parent_pid == getpid() &&
+			 msg.event.event_data.fork.parent_tgid == getpid() &&
+			 msg.event.event_data.fork.child_tgid == pid);
+	fork_seq = msg.cn.seq;
+
+	TEST_RES(recv_event(pid, PROC_EVENT_EXIT),
+		 msg.event.event_data.exit.process_tgid == pid &&
+			 msg.event.event_data.exit.exit_code == (7 << 8) &&
+			 msg.event.event_data.exit.exit_signal == SIGCHLD &&
+			 msg.event.event_data.exit.parent_pid == getpid() &&
+			 msg.cn.seq > fork_seq);
+
+	TEST_RES(wait(NULL), _ret == pid);
+}
+END_TEST()
+
+FN_TEST(ignore)
+{
+	pid_t pid;
+
+	TEST_SUCC(send_op(PROC_CN_MCAST_IGNORE));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(0);
+	TEST_RES(wait(NULL), _ret == pid);
+
+	// No one listens, so no events are generated.
+	TEST_ERRNO(recv(sk_proc, &msg, sizeof(msg), MSG_DONTWAIT), EAGAIN);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(sk_proc));
+}
+END_SETUP()
@@ -0,0 +1,407 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <arpa/inet.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <sys/socket.h>
+#include <unistd.h>
+#include <linux/fib_rules.h>
+#include <linux/netlink.h>
+#include <linux/rtnetlink.h>
+
+#include "test.h"
+
+#define BUFFER_SIZE 8192
+
+#define ETHER_NAME "eth0"
+#define LOOPBACK_NAME "lo"
+
+static int rtnl_sk;
+static int lo_index;
+static int eth0_index;
+
+FN_SETUP(rtnl_sk)
+{
+	rtnl_sk = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	lo_index = CHECK(if_nametoindex(LOOPBACK_NAME));
+	eth0_index = CHECK(if_nametoindex(ETHER_NAME));
+}
+END_SETUP()
+
+struct rtnl_req {
+	struct nlmsghdr hdr;
+	union {
+		struct rtmsg rtm;
+		struct fib_rule_hdr frh;
+	};
+	char attrs[64];
+};
+
+static void add_attr(struct rtnl_req *req, unsigned short type,
+		     const void *data, size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_addr_attr(struct rtnl_req *req, unsigned short type,
+			  const char *addr)
+{
+	struct in_addr in_addr;
+
+	inet_pton(AF_INET, addr, &in_addr);
+	add_attr(req, type, &in_addr, sizeof(in_addr));
+}
+
+static void add_u32_attr(struct rtnl_req *req, unsigned short type,
+			 unsigned int value)
+{
+	add_attr(req, type, &value, sizeof(value));
+}
+
+// Sends the request and returns the error code in the acknowledgment.
+static int rtnl_ack(struct rtnl_req *req)
+{
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+	if (send(rtnl_sk, req, req->hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != NLMSG_ERROR)
+		return -1;
+
+	return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+}
+
+static void init_route_req(struct rtnl_req *req, int type, int flags,
+			   int route_type, const char *dst, int dst_len,
+			   unsigned int table)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct rtmsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->rtm.rtm_family = AF_INET;
+	req->rtm.rtm_dst_len = dst_len;
+	req->rtm.rtm_table = RT_TABLE_UNSPEC;
+	req->rtm.rtm_protocol = RTPROT_STATIC;
+	req->rtm.rtm_type = route_type;
+
+	add_addr_attr(req, RTA_DST, dst);
+	add_u32_attr(req, RTA_TABLE, table);
+}
+
+static void init_rule_req(struct rtnl_req *req, int type, int flags,
+			  int action, const char *dst, int dst_len,
+			  unsigned int table, unsigned int priority)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct fib_rule_hdr));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->frh.family = AF_INET;
+	req->frh.dst_len = dst_len;
+	req->frh.action = action;
+
+	add_addr_attr(req, FRA_DST, dst);
+	add_u32_attr(req, FRA_TABLE, table);
+	add_u32_attr(req, FRA_PRIORITY, priority);
+}
+
+// The result of `ip route get`.
+struct route_result {
+	unsigned int table;
+	int oif;
+	struct in_addr gateway;
+};
+
+// Looks up the route to the destination and returns the error code.
+static int get_route(const char *dst, struct route_result *result)
+{
+	struct rtnl_req req;
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct rtmsg *rtm = NLMSG_DATA(nlh);
+	struct rtattr *rta;
+	int rta_len;
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct rtmsg));
+	req.hdr.nlmsg_type = RTM_GETROUTE;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST;
+	req.rtm.rtm_family = AF_INET;
+	req.rtm.rtm_dst_len = 32;
+	add_addr_attr(&req, RTA_DST, dst);
+
+	if (send(rtnl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(rtnl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type == NLMSG_ERROR)
+		return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+	if (nlh->nlmsg_type != RTM_NEWROUTE)
+		return -1;
+
+	memset(result, 0, sizeof(*result));
+	rta = RTM_RTA(rtm);
+	rta_len = RTM_PAYLOAD(nlh);
+	for (; RTA_OK(rta, rta_len); rta = RTA_NEXT(rta, rta_len)) {
+		if (rta->rta_type == RTA_TABLE)
+			memcpy(&result->table, RTA_DATA(rta), 4);
+		if (rta->rta_type == RTA_OIF)
+			memcpy(&result->oif, RTA_DATA(rta), 4);
+		if (rta->rta_type == RTA_GATEWAY)
+			memcpy(&result->gateway, RTA_DATA(rta), 4);
+	}
+
+	return 0;
+}
+
+static int is_gateway(struct route_result *result, const char *gateway)
+{
+	struct in_addr addr;
+
+	inet_pton(AF_INET, gateway, &addr);
+	return result->gateway.s_addr == addr.s_addr;
+}
+
+// Returns the number of IPv4 rules that have the priority and look up the table.
+static int find_rule(unsigned int priority, unsigned int table)
+{
+	struct rtnl_req req;
+	char buffer[BUFFER_SIZE];
+	int found = 0;
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct fib_rule_hdr));
+	req.hdr.nlmsg_type = RTM_GETRULE;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_DUMP;
+	req.frh.family = AF_INET;
+
+	if (send(rtnl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		long len = recv(rtnl_sk, buffer, sizeof(buffer), 0);
+		struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+		if (len < 0)
+			return -1;
+
+		for (; NLMSG_OK(nlh, len); nlh = NLMSG_NEXT(nlh, len)) {
+			struct fib_rule_hdr *frh = NLMSG_DATA(nlh);
+			struct rtattr *rta =
+				(struct rtattr *)((char *)frh +
+						  NLMSG_ALIGN(sizeof(*frh)));
+			int rta_len = NLMSG_PAYLOAD(nlh, sizeof(*frh));
+			unsigned int rule_priority = 0, rule_table = 0;
+
+			if (nlh->nlmsg_type == NLMSG_DONE)
+				return found;
+			if (nlh->nlmsg_type != RTM_NEWRULE ||
+			    frh->family != AF_INET)
+				return -1;
+
+			for (; RTA_OK(rta, rta_len);
+			     rta = RTA_NEXT(rta, rta_len)) {
+				if (rta->rta_type == FRA_PRIORITY)
+					memcpy(&rule_priority, RTA_DATA(rta),
+					       4);
+				if (rta->rta_type == FRA_TABLE)
+					memcpy(&rule_table, RTA_DATA(rta), 4);
+			}
+
+			if (frh->action == FR_ACT_TO_TBL &&
+			    rule_priority == priority && rule_table == table)
+				found++;
+		}
+	}
+}
+
+FN_TEST(default_rules)
+{
+	TEST_RES(find_rule(0, RT_TABLE_LOCAL), _ret == 1);
+	TEST_RES(find_rule(32766, RT_TABLE_MAIN), _ret == 1);
+	TEST_RES(find_rule(32767, RT_TABLE_DEFAULT), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(new_del_rule)
+{
+	struct rtnl_req req;
+
+	init_rule_req(&req, RTM_NEWRULE, NLM_F_CREATE | NLM_F_EXCL,
+		      FR_ACT_TO_TBL, "10.9.0.0", 16, 100, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -EEXIST);
+	TEST_RES(find_rule(1000, 100), _ret == 1);
+
+	init_rule_req(&req, RTM_DELRULE, 0, FR_ACT_TO_TBL, "10.9.0.0", 16,
+		      100, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -ENOENT);
+	TEST_RES(find_rule(1000, 100), _ret == 0);
+
+	// The table must be specified
+	init_rule_req(&req, RTM_NEWRULE, NLM_F_CREATE, FR_ACT_TO_TBL,
+		      "10.9.0.0", 16, 0, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == -EINVAL);
+}
+END_TEST()
+
+FN_TEST(policy_routing)
+{
+	struct rtnl_req req;
+	struct route_result result;
+
+	// ip route add 10.9.0.0/16 dev lo table 100
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_UNICAST, "10.9.0.0", 16, 100);
+	add_u32_attr(&req, RTA_OIF, lo_index);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	// Without any rules, the route in the main table is used
+	TEST_RES(get_route("10.9.1.1", &result),
+		 _ret == 0 && result.table == RT_TABLE_MAIN &&
+			 result.oif == eth0_index &&
+			 is_gateway(&result, "10.0.2.2"));
+
+	// ip rule add to 10.9.0.0/16 lookup 100 pref 1000
+	init_rule_req(&req, RTM_NEWRULE, NLM_F_CREATE | NLM_F_EXCL,
+		      FR_ACT_TO_TBL, "10.9.0.0", 16, 100, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_RES(get_route("10.9.1.1", &result),
+		 _ret == 0 && result.table == 100 && result.oif == lo_index &&
+			 result.gateway.s_addr == 0);
+	TEST_RES(get_route("10.10.1.1", &result),
+		 _ret == 0 && result.table == RT_TABLE_MAIN &&
+			 result.oif == eth0_index);
+
+	init_rule_req(&req, RTM_DELRULE, 0, FR_ACT_TO_TBL, "10.9.0.0", 16,
+		      100, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_UNICAST, "10.9.0.0", 16,
+		       100);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	TEST_RES(rtnl_ack(&req), _ret == -ESRCH);
+}
+END_TEST()
+
+FN_TEST(route_metrics)
+{
+	struct rtnl_req req;
+	struct route_result result;
+
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_UNICAST, "10.8.0.0", 16, RT_TABLE_MAIN);
+	add_addr_attr(&req, RTA_GATEWAY, "10.0.2.3");
+	add_u32_attr(&req, RTA_PRIORITY, 10);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_UNICAST, "10.8.0.0", 16, RT_TABLE_MAIN);
+	add_addr_attr(&req, RTA_GATEWAY, "10.0.2.4");
+	add_u32_attr(&req, RTA_PRIORITY, 5);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	// The route with the lowest metric is preferred
+	TEST_RES(get_route("10.8.0.1", &result),
+		 _ret == 0 && is_gateway(&result, "10.0.2.4"));
+
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_UNICAST, "10.8.0.0", 16,
+		       RT_TABLE_MAIN);
+	add_u32_attr(&req, RTA_PRIORITY, 5);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_RES(get_route("10.8.0.1", &result),
+		 _ret == 0 && is_gateway(&result, "10.0.2.3"));
+
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_UNICAST, "10.8.0.0", 16,
+		       RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	// The longest prefix wins over the default route
+	TEST_RES(get_route("10.8.0.1", &result),
+		 _ret == 0 && is_gateway(&result, "10.0.2.2"));
+}
+END_TEST()
+
+static int udp_connect(const char *addr)
+{
+	struct sockaddr_in sin = { .sin_family = AF_INET,
+				   .sin_port = htons(8080) };
+	int sk, ret;
+
+	inet_pton(AF_INET, addr, &sin.sin_addr);
+
+	sk = socket(PF_INET, SOCK_DGRAM, 0);
+	if (sk < 0)
+		return -1;
+	ret = connect(sk, (struct sockaddr *)&sin, sizeof(sin));
+	close(sk);
+
+	return ret;
+}
+
+FN_TEST(reject_routes)
+{
+	struct rtnl_req req;
+
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_BLACKHOLE, "10.7.1.0", 24, RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_PROHIBIT, "10.7.2.0", 24, RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	init_route_req(&req, RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL,
+		       RTN_UNREACHABLE, "10.7.3.0", 24, RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_ERRNO(udp_connect("10.7.1.1"), EINVAL);
+	TEST_ERRNO(udp_connect("10.7.2.1"), EACCES);
+	TEST_ERRNO(udp_connect("10.7.3.1"), EHOSTUNREACH);
+	TEST_SUCC(udp_connect("10.7.4.1"));
+
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_BLACKHOLE, "10.7.1.0", 24,
+		       RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_PROHIBIT, "10.7.2.0", 24,
+		       RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+	init_route_req(&req, RTM_DELROUTE, 0, RTN_UNREACHABLE, "10.7.3.0", 24,
+		       RT_TABLE_MAIN);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_SUCC(udp_connect("10.7.1.1"));
+}
+END_TEST()
+
+FN_TEST(reject_rules)
+{
+	struct rtnl_req req;
+
+	// ip rule add to 10.6.0.0/16 prohibit pref 1000
+	init_rule_req(&req, RTM_NEWRULE, NLM_F_CREATE | NLM_F_EXCL,
+		      FR_ACT_PROHIBIT, "10.6.0.0", 16, 0, 1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_ERRNO(udp_connect("10.6.1.1"), EACCES);
+
+	init_rule_req(&req, RTM_DELRULE, 0, FR_ACT_PROHIBIT, "10.6.0.0", 16, 0,
+		      1000);
+	TEST_RES(rtnl_ack(&req), _ret == 0);
+
+	TEST_SUCC(udp_connect("10.6.1.1"));
+}
+END_TEST()
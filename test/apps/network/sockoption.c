@@ -2,10 +2,13 @@
 
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <sys/socket.h>
 #include <netinet/in.h>
 #include <netinet/tcp.h>
 #include <unistd.h>
+#include <fcntl.h>
+#include <poll.h>
 #include <arpa/inet.h>
 #include "test.h"
 
@@ -279,6 +282,230 @@ FN_TEST(keepidle)
 }
 END_TEST()
 
+FN_TEST(keepintvl_keepcnt)
+{
+	int option = 1;
+	int value;
+	socklen_t value_len = sizeof(value);
+
+	// 1. Check default values
+	refresh_connection();
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			    &value_len),
+		 value == 75);
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			    &value_len),
+		 value == 9);
+
+	// 2. Set and get values
+	value = 10;
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			 sizeof(value)));
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			    &value_len),
+		 value == 10);
+	value = 3;
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			 sizeof(value)));
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			    &value_len),
+		 value == 3);
+
+	// 3. Set invalid values
+	value = 0;
+	TEST_ERRNO(setsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			      sizeof(value)),
+		   EINVAL);
+	value = 128;
+	TEST_ERRNO(setsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			      sizeof(value)),
+		   EINVAL);
+
+	// 4. Inherit from the listening socket
+	CHECK(setsockopt(sk_listen, SOL_SOCKET, SO_KEEPALIVE, &option,
+			 sizeof(option)));
+	value = 20;
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			 sizeof(value)));
+	value = 5;
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			 sizeof(value)));
+
+	refresh_connection();
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			    &value_len),
+		 value == 75);
+	TEST_RES(getsockopt(sk_accepted, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			    &value_len),
+		 value == 20);
+	TEST_RES(getsockopt(sk_accepted, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			    &value_len),
+		 value == 5);
+
+	option = 0;
+	CHECK(setsockopt(sk_listen, SOL_SOCKET, SO_KEEPALIVE, &option,
+			 sizeof(option)));
+	value = 75;
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_KEEPINTVL, &value,
+			 sizeof(value)));
+	value = 9;
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_KEEPCNT, &value,
+			 sizeof(value)));
+}
+END_TEST()
+
+FN_TEST(cork)
+{
+	int option = 1;
+	int cork;
+	socklen_t cork_len = sizeof(cork);
+	char buf[4];
+	struct pollfd pfd = { .fd = -1, .events = POLLIN };
+
+	// 1. Check default values
+	refresh_connection();
+	pfd.fd = sk_accepted;
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CORK, &cork,
+			    &cork_len),
+		 cork == 0);
+
+	// 2. Partial segments are held back while the socket is corked
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CORK, &option,
+			 sizeof(option)));
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CORK, &cork,
+			    &cork_len),
+		 cork == 1);
+	TEST_RES(send(sk_connected, "abc", 3, 0), _ret == 3);
+	TEST_ERRNO(recv(sk_accepted, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	// 3. Partial segments are sent after the socket is uncorked
+	option = 0;
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CORK, &option,
+			 sizeof(option)));
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1);
+	TEST_RES(recv(sk_accepted, buf, sizeof(buf), 0),
+		 _ret == 3 && memcmp(buf, "abc", 3) == 0);
+
+	// 4. Partial segments are sent after being held back for 200ms
+	option = 1;
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CORK, &option,
+			 sizeof(option)));
+	TEST_RES(send(sk_connected, "def", 3, 0), _ret == 3);
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1);
+	TEST_RES(recv(sk_accepted, buf, sizeof(buf), 0),
+		 _ret == 3 && memcmp(buf, "def", 3) == 0);
+}
+END_TEST()
+
+#define REUSEPORT_PORT htons(0x1243)
+#define REUSEPORT_CONNS 16
+
+FN_TEST(reuseport)
+{
+	int option = 1;
+	int sk_listeners[2];
+	int sk_clients[REUSEPORT_CONNS];
+	int sk_other;
+	int accepted[2] = { 0, 0 };
+	struct sockaddr_in addr = listen_addr;
+	int i, j, sk;
+
+	addr.sin_port = REUSEPORT_PORT;
+
+	// 1. Create a reuseport group
+	for (i = 0; i < 2; i++) {
+		sk_listeners[i] = CHECK(socket(AF_INET, SOCK_STREAM, 0));
+		CHECK(setsockopt(sk_listeners[i], SOL_SOCKET, SO_REUSEPORT,
+				 &option, sizeof(option)));
+		TEST_SUCC(bind(sk_listeners[i], (struct sockaddr *)&addr,
+			       sizeof(addr)));
+		TEST_SUCC(listen(sk_listeners[i], REUSEPORT_CONNS));
+		CHECK(fcntl(sk_listeners[i], F_SETFL, O_NONBLOCK));
+	}
+
+	// 2. Sockets without SO_REUSEPORT cannot join the group
+	sk_other = CHECK(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_ERRNO(bind(sk_other, (struct sockaddr *)&addr, sizeof(addr)),
+		   EADDRINUSE);
+	close(sk_other);
+
+	// 3. New connections are distributed across the listeners
+	for (i = 0; i < REUSEPORT_CONNS; i++) {
+		sk_clients[i] = CHECK(socket(AF_INET, SOCK_STREAM, 0));
+		CHECK(connect(sk_clients[i], (struct sockaddr *)&addr,
+			      sizeof(addr)));
+	}
+
+	for (i = 0; i < 2; i++) {
+		while ((sk = accept(sk_listeners[i], NULL, NULL)) >= 0) {
+			accepted[i]++;
+			close(sk);
+		}
+	}
+	TEST_RES(accepted[0] + accepted[1],
+		 _ret == REUSEPORT_CONNS && accepted[0] > 0 && accepted[1] > 0);
+
+	for (j = 0; j < REUSEPORT_CONNS; j++)
+		close(sk_clients[j]);
+	for (i = 0; i < 2; i++)
+		close(sk_listeners[i]);
+}
+END_TEST()
+
+FN_TEST(congestion)
+{
+	char name[16], default_name[16];
+	socklen_t name_len = sizeof(name);
+	const char *other_name;
+
+	// 1. Check default values
+	//
+	// The default algorithm differs between systems (e.g., "cubic" on most
+	// Linux systems and "reno" on Asterinas), but it is the same for all
+	// sockets.
+	refresh_connection();
+	CHECK(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION,
+			 default_name, &name_len));
+	name_len = sizeof(name);
+	TEST_RES(getsockopt(sk_accepted, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, default_name) == 0);
+
+	// 2. Set and get values
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, "reno",
+			 strlen("reno")));
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, "reno") == 0);
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, "cubic",
+			 strlen("cubic")));
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, "cubic") == 0);
+	TEST_ERRNO(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION,
+			      "unknown", strlen("unknown")),
+		   ENOENT);
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, "cubic") == 0);
+
+	// 3. Inherit from the listening socket
+	other_name = strcmp(default_name, "reno") == 0 ? "cubic" : "reno";
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_CONGESTION, other_name,
+			 strlen(other_name)));
+	refresh_connection();
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, default_name) == 0);
+	TEST_RES(getsockopt(sk_accepted, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, other_name) == 0);
+
+	CHECK(setsockopt(sk_listen, IPPROTO_TCP, TCP_CONGESTION, default_name,
+			 strlen(default_name)));
+}
+END_TEST()
+
 FN_TEST(ip_tos)
 {
 	int tos;
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <sys/poll.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define TCP_SYNCOOKIES "/proc/sys/net/ipv4/tcp_syncookies"
+
+static struct sockaddr_in sk_addr;
+
+static int write_syncookies(const char *value)
+{
+	int fd, len;
+
+	fd = open(TCP_SYNCOOKIES, O_WRONLY);
+	if (fd < 0)
+		return -1;
+
+	len = write(fd, value, strlen(value));
+	close(fd);
+
+	return len;
+}
+
+static int read_syncookies(char *buf, size_t len)
+{
+	int fd, ret;
+
+	memset(buf, 0, len);
+
+	fd = open(TCP_SYNCOOKIES, O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	ret = read(fd, buf, len - 1);
+	close(fd);
+
+	return ret;
+}
+
+static int new_listener(unsigned short port, int backlog)
+{
+	int sk;
+
+	sk = socket(PF_INET, SOCK_STREAM, 0);
+	if (sk < 0)
+		return -1;
+
+	sk_addr.sin_family = AF_INET;
+	sk_addr.sin_port = htons(port);
+	sk_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+	if (bind(sk, (struct sockaddr *)&sk_addr, sizeof(sk_addr)) < 0 ||
+	    listen(sk, backlog) < 0) {
+		close(sk);
+		return -1;
+	}
+
+	return sk;
+}
+
+// Starts a non-blocking connection to `sk_addr`.
+static int start_connect(void)
+{
+	int sk;
+
+	sk = socket(PF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+	if (sk < 0)
+		return -1;
+
+	if (connect(sk, (struct sockaddr *)&sk_addr, sizeof(sk_addr)) < 0 &&
+	    errno != EINPROGRESS) {
+		close(sk);
+		return -1;
+	}
+
+	return sk;
+}
+
+static int poll_events(int sk, short events, int timeout)
+{
+	struct pollfd pfd = { .fd = sk, .events = events };
+
+	if (poll(&pfd, 1, timeout) < 0)
+		return -1;
+
+	return pfd.revents;
+}
+
+FN_TEST(syncookies_sysctl)
+{
+	char buf[16];
+
+	TEST_RES(read_syncookies(buf, sizeof(buf)), strcmp(buf, "1\n") == 0);
+
+	TEST_ERRNO(write_syncookies("3"), EINVAL);
+	TEST_ERRNO(write_syncookies("on"), EINVAL);
+
+	TEST_RES(write_syncookies("2\n"), _ret == 2);
+	TEST_RES(read_syncookies(buf, sizeof(buf)), strcmp(buf, "2\n") == 0);
+	TEST_RES(write_syncookies("1"), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(syncookies_always)
+{
+	int sk_listen, sk_connect, sk_accept;
+	char buf[8];
+
+	// All connections are established with SYN cookies, so the SYN
+	// segments do not occupy the SYN queue.
+	TEST_RES(write_syncookies("2"), _ret == 1);
+
+	sk_listen = TEST_SUCC(new_listener(0x1250, 1));
+	sk_connect = TEST_SUCC(start_connect());
+	TEST_RES(poll_events(sk_connect, POLLOUT, 1000), _ret == POLLOUT);
+	TEST_RES(poll_events(sk_listen, POLLIN, 1000), _ret == POLLIN);
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+
+	TEST_RES(write(sk_connect, "hello", 5), _ret == 5);
+	TEST_RES(poll_events(sk_accept, POLLIN, 1000), _ret == POLLIN);
+	TEST_RES(read(sk_accept, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+
+	TEST_RES(write(sk_accept, "world", 5), _ret == 5);
+	TEST_RES(poll_events(sk_connect, POLLIN, 1000), _ret & POLLIN);
+	TEST_RES(read(sk_connect, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "world", 5) == 0);
+
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+	TEST_SUCC(close(sk_listen));
+
+	TEST_RES(write_syncookies("1"), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(accept_queue_full)
+{
+	int sk_listen, sk_connect[4];
+
+	TEST_RES(write_syncookies("0"), _ret == 1);
+
+	// The accept queue can hold one more connection than the backlog.
+	sk_listen = TEST_SUCC(new_listener(0x1251, 1));
+	for (int i = 0; i < 2; ++i) {
+		sk_connect[i] = TEST_SUCC(start_connect());
+		TEST_RES(poll_events(sk_connect[i], POLLOUT, 1000),
+			 _ret == POLLOUT);
+	}
+
+	// The SYN segment is dropped if the accept queue is full.
+	sk_connect[2] = TEST_SUCC(start_connect());
+	TEST_RES(poll_events(sk_connect[2], POLLOUT, 100), _ret == 0);
+
+	// Calling `listen()` again updates the backlog.
+	TEST_SUCC(listen(sk_listen, 3));
+	sk_connect[3] = TEST_SUCC(start_connect());
+	TEST_RES(poll_events(sk_connect[3], POLLOUT, 1000), _ret == POLLOUT);
+
+	for (int i = 0; i < 4; ++i)
+		TEST_SUCC(close(sk_connect[i]));
+	TEST_SUCC(close(sk_listen));
+
+	TEST_RES(write_syncookies("1"), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(defer_accept_option)
+{
+	int sk, val;
+	socklen_t len = sizeof(val);
+
+	sk = TEST_SUCC(socket(PF_INET, SOCK_STREAM, 0));
+
+	// The value is rounded up to the timeout of SYN-ACK retransmissions.
+	val = 5;
+	TEST_SUCC(setsockopt(sk, IPPROTO_TCP, TCP_DEFER_ACCEPT, &val,
+			     sizeof(val)));
+	TEST_RES(getsockopt(sk, IPPROTO_TCP, TCP_DEFER_ACCEPT, &val, &len),
+		 val == 7);
+
+	val = -1;
+	TEST_SUCC(setsockopt(sk, IPPROTO_TCP, TCP_DEFER_ACCEPT, &val,
+			     sizeof(val)));
+	TEST_RES(getsockopt(sk, IPPROTO_TCP, TCP_DEFER_ACCEPT, &val, &len),
+		 val == 0);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(defer_accept)
+{
+	int sk_listen, sk_connect, sk_accept, val;
+	char buf[8];
+
+	sk_listen = TEST_SUCC(new_listener(0x1252, 4));
+	val = 10;
+	TEST_SUCC(setsockopt(sk_listen, IPPROTO_TCP, TCP_DEFER_ACCEPT, &val,
+			     sizeof(val)));
+
+	// The handshake completes, but the connection cannot be accepted
+	// until data arrives.
+	sk_connect = TEST_SUCC(start_connect());
+	TEST_RES(poll_events(sk_connect, POLLOUT, 1000), _ret == POLLOUT);
+	TEST_RES(poll_events(sk_listen, POLLIN, 100), _ret == 0);
+	TEST_ERRNO(accept4(sk_listen, NULL, NULL, SOCK_NONBLOCK), EAGAIN);
+
+	TEST_RES(write(sk_connect, "hello", 5), _ret == 5);
+	TEST_RES(poll_events(sk_listen, POLLIN, 1000), _ret == POLLIN);
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+	TEST_RES(read(sk_accept, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+	TEST_SUCC(close(sk_listen));
+}
+END_TEST()
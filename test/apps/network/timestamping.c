@@ -0,0 +1,411 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <unistd.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <linux/errqueue.h>
+#include <linux/net_tstamp.h>
+#include <time.h>
+
+#include "test.h"
+
+#ifndef SO_ZEROCOPY
+#define SO_ZEROCOPY 60
+#endif
+
+#ifndef MSG_ZEROCOPY
+#define MSG_ZEROCOPY 0x4000000
+#endif
+
+static int sk_send;
+static int sk_recv;
+
+FN_SETUP(sockets)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET,
+				    .sin_port = htons(0x3456) };
+
+	CHECK(inet_aton("127.0.0.1", &addr.sin_addr));
+
+	sk_recv = CHECK(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	CHECK(bind(sk_recv, (struct sockaddr *)&addr, sizeof(addr)));
+
+	sk_send = CHECK(socket(PF_INET, SOCK_DGRAM | SOCK_NONBLOCK, 0));
+	CHECK(connect(sk_send, (struct sockaddr *)&addr, sizeof(addr)));
+}
+END_SETUP()
+
+static int get_int_option(int sk, int name)
+{
+	int val = -1;
+	socklen_t len = sizeof(val);
+
+	if (getsockopt(sk, SOL_SOCKET, name, &val, &len) < 0)
+		return -1;
+	if (len != sizeof(val))
+		return -1;
+
+	return val;
+}
+
+static int set_int_option(int sk, int name, int val)
+{
+	return setsockopt(sk, SOL_SOCKET, name, &val, sizeof(val));
+}
+
+static char recv_buf[16];
+static char recv_control[512];
+static struct msghdr recv_msg;
+
+static int do_recv(int sk, int flags)
+{
+	static struct iovec iov;
+
+	memset(recv_buf, 0, sizeof(recv_buf));
+	memset(recv_control, 0, sizeof(recv_control));
+
+	iov.iov_base = recv_buf;
+	iov.iov_len = sizeof(recv_buf);
+
+	memset(&recv_msg, 0, sizeof(recv_msg));
+	recv_msg.msg_iov = &iov;
+	recv_msg.msg_iovlen = 1;
+	recv_msg.msg_control = recv_control;
+	recv_msg.msg_controllen = sizeof(recv_control);
+
+	return recvmsg(sk, &recv_msg, flags);
+}
+
+static struct cmsghdr *find_cmsg(int level, int type)
+{
+	struct cmsghdr *cmsg;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg))
+		if (cmsg->cmsg_level == level && cmsg->cmsg_type == type)
+			return cmsg;
+
+	return NULL;
+}
+
+static int count_cmsgs(void)
+{
+	struct cmsghdr *cmsg;
+	int count = 0;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg))
+		count++;
+
+	return count;
+}
+
+// Checks whether the time is in the last ten seconds.
+static int is_recent(time_t sec)
+{
+	struct timespec now;
+
+	if (clock_gettime(CLOCK_REALTIME, &now) < 0)
+		return 0;
+
+	return sec <= now.tv_sec && sec + 10 >= now.tv_sec;
+}
+
+static int is_recent_timestamping(void)
+{
+	struct cmsghdr *cmsg = find_cmsg(SOL_SOCKET, SCM_TIMESTAMPING);
+	struct scm_timestamping *tss;
+
+	if (cmsg == NULL ||
+	    cmsg->cmsg_len != CMSG_LEN(sizeof(struct scm_timestamping)))
+		return 0;
+
+	tss = (struct scm_timestamping *)CMSG_DATA(cmsg);
+	return is_recent(tss->ts[0].tv_sec) && tss->ts[2].tv_sec == 0 &&
+	       tss->ts[2].tv_nsec == 0;
+}
+
+static struct sock_extended_err *find_extended_err(void)
+{
+	struct cmsghdr *cmsg = find_cmsg(SOL_IP, IP_RECVERR);
+
+	if (cmsg == NULL || cmsg->cmsg_len < CMSG_LEN(sizeof(
+						     struct sock_extended_err)))
+		return NULL;
+
+	return (struct sock_extended_err *)CMSG_DATA(cmsg);
+}
+
+static int wait_for_errors(int sk)
+{
+	struct pollfd pfd = { .fd = sk, .events = 0 };
+
+	return poll(&pfd, 1, 1000) == 1 && (pfd.revents & POLLERR);
+}
+
+FN_TEST(timestamp_options)
+{
+	struct so_timestamping ts_opt;
+	socklen_t len;
+	int flags;
+
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMP), _ret == 0);
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPNS), _ret == 0);
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPING), _ret == 0);
+
+	// `SO_TIMESTAMP` and `SO_TIMESTAMPNS` are mutually exclusive
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMP, 1));
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMP), _ret == 1);
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPNS, 1));
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMP), _ret == 0);
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPNS), _ret == 1);
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMP, 0));
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPNS), _ret == 0);
+
+	flags = SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE;
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPING, flags));
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPING), _ret == flags);
+
+	len = sizeof(ts_opt);
+	memset(&ts_opt, 0xff, sizeof(ts_opt));
+	TEST_RES(getsockopt(sk_recv, SOL_SOCKET, SO_TIMESTAMPING, &ts_opt,
+			    &len),
+		 len == sizeof(ts_opt) && ts_opt.flags == flags &&
+			 ts_opt.bind_phc == 0);
+
+	TEST_ERRNO(set_int_option(sk_recv, SO_TIMESTAMPING, 1 << 24), EINVAL);
+	TEST_ERRNO(set_int_option(sk_recv, SO_TIMESTAMPING,
+				  SOF_TIMESTAMPING_OPT_STATS),
+		   EINVAL);
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPING), _ret == flags);
+
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPING, 0));
+	TEST_RES(get_int_option(sk_recv, SO_TIMESTAMPING), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(rx_timestamps)
+{
+	struct cmsghdr *cmsg;
+	struct timespec *ts;
+	int flags;
+
+	// No timestamps
+	TEST_RES(send(sk_send, "a", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && count_cmsgs() == 0);
+
+	// `SO_TIMESTAMPNS`
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPNS, 1));
+	TEST_RES(send(sk_send, "b", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0),
+		 _ret == 1 && count_cmsgs() == 1 &&
+			 (cmsg = find_cmsg(SOL_SOCKET, SCM_TIMESTAMPNS)) &&
+			 cmsg->cmsg_len == CMSG_LEN(sizeof(struct timespec)) &&
+			 (ts = (struct timespec *)CMSG_DATA(cmsg)) &&
+			 is_recent(ts->tv_sec) && ts->tv_nsec < 1000000000);
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPNS, 0));
+
+	// `SO_TIMESTAMPING`
+	flags = SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE;
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPING, flags));
+	TEST_RES(send(sk_send, "c", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && count_cmsgs() == 1 &&
+					      is_recent_timestamping());
+
+	// The timestamps are not reported without `SOF_TIMESTAMPING_SOFTWARE`
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPING,
+				 SOF_TIMESTAMPING_RX_SOFTWARE));
+	TEST_RES(send(sk_send, "d", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && count_cmsgs() == 0);
+
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPING, 0));
+}
+END_TEST()
+
+FN_TEST(tx_timestamps)
+{
+	struct sock_extended_err *serr;
+	int flags;
+
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	flags = SOF_TIMESTAMPING_TX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE |
+		SOF_TIMESTAMPING_OPT_ID | SOF_TIMESTAMPING_OPT_TSONLY;
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING, flags));
+
+	TEST_RES(send(sk_send, "a", 1, 0), _ret == 1);
+	TEST_RES(send(sk_send, "b", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'a');
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'b');
+
+	TEST_RES(wait_for_errors(sk_send), _ret);
+
+	TEST_RES(do_recv(sk_send, MSG_ERRQUEUE),
+		 _ret == 0 && (recv_msg.msg_flags & MSG_ERRQUEUE) &&
+			 is_recent_timestamping() &&
+			 (serr = find_extended_err()) &&
+			 serr->ee_errno == ENOMSG &&
+			 serr->ee_origin == SO_EE_ORIGIN_TIMESTAMPING &&
+			 serr->ee_info == SCM_TSTAMP_SND && serr->ee_data == 0);
+	TEST_RES(do_recv(sk_send, MSG_ERRQUEUE),
+		 _ret == 0 && (recv_msg.msg_flags & MSG_ERRQUEUE) &&
+			 is_recent_timestamping() &&
+			 (serr = find_extended_err()) &&
+			 serr->ee_errno == ENOMSG &&
+			 serr->ee_origin == SO_EE_ORIGIN_TIMESTAMPING &&
+			 serr->ee_info == SCM_TSTAMP_SND && serr->ee_data == 1);
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING, 0));
+
+	// Only the messages with timestamping flags are timestamped
+	TEST_RES(send(sk_send, "c", 1, 0), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'c');
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+}
+END_TEST()
+
+static int send_with_flags(int sk, const char *buf, size_t len, __u32 flags)
+{
+	struct iovec iov = { .iov_base = (void *)buf, .iov_len = len };
+	char control[CMSG_SPACE(sizeof(__u32))];
+	struct msghdr msg = {
+		.msg_iov = &iov,
+		.msg_iovlen = 1,
+		.msg_control = control,
+		.msg_controllen = sizeof(control),
+	};
+	struct cmsghdr *cmsg;
+
+	memset(control, 0, sizeof(control));
+	cmsg = CMSG_FIRSTHDR(&msg);
+	cmsg->cmsg_level = SOL_SOCKET;
+	cmsg->cmsg_type = SCM_TIMESTAMPING;
+	cmsg->cmsg_len = CMSG_LEN(sizeof(__u32));
+	memcpy(CMSG_DATA(cmsg), &flags, sizeof(__u32));
+
+	return sendmsg(sk, &msg, 0);
+}
+
+FN_TEST(tx_timestamps_cmsg)
+{
+	struct sock_extended_err *serr;
+
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING,
+				 SOF_TIMESTAMPING_SOFTWARE |
+					 SOF_TIMESTAMPING_OPT_TSONLY));
+
+	// Only the flags that request the transmit timestamps are allowed
+	TEST_ERRNO(send_with_flags(sk_send, "a", 1,
+				   SOF_TIMESTAMPING_RX_SOFTWARE),
+		   EINVAL);
+
+	TEST_RES(send_with_flags(sk_send, "b", 1,
+				 SOF_TIMESTAMPING_TX_SOFTWARE),
+		 _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'b');
+
+	TEST_RES(wait_for_errors(sk_send), _ret);
+
+	TEST_RES(do_recv(sk_send, MSG_ERRQUEUE),
+		 _ret == 0 && is_recent_timestamping() &&
+			 (serr = find_extended_err()) &&
+			 serr->ee_origin == SO_EE_ORIGIN_TIMESTAMPING &&
+			 serr->ee_info == SCM_TSTAMP_SND);
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING, 0));
+}
+END_TEST()
+
+FN_TEST(udp_zerocopy)
+{
+	struct sock_extended_err *serr;
+
+	TEST_RES(get_int_option(sk_send, SO_ZEROCOPY), _ret == 0);
+
+	// `MSG_ZEROCOPY` is ignored without `SO_ZEROCOPY`
+	TEST_RES(send(sk_send, "a", 1, MSG_ZEROCOPY), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'a');
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(set_int_option(sk_send, SO_ZEROCOPY, 1));
+	TEST_RES(get_int_option(sk_send, SO_ZEROCOPY), _ret == 1);
+
+	TEST_RES(send(sk_send, "b", 1, MSG_ZEROCOPY), _ret == 1);
+	TEST_RES(send(sk_send, "c", 1, MSG_ZEROCOPY), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'b');
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'c');
+
+	TEST_RES(wait_for_errors(sk_send), _ret);
+
+	// The completions of adjacent transmissions are merged
+	TEST_RES(do_recv(sk_send, MSG_ERRQUEUE),
+		 _ret == 0 && (recv_msg.msg_flags & MSG_ERRQUEUE) &&
+			 count_cmsgs() == 1 && (serr = find_extended_err()) &&
+			 serr->ee_errno == 0 &&
+			 serr->ee_origin == SO_EE_ORIGIN_ZEROCOPY &&
+			 serr->ee_code == SO_EE_CODE_ZEROCOPY_COPIED &&
+			 serr->ee_info == 0 && serr->ee_data == 1);
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_RES(send(sk_send, "d", 1, MSG_ZEROCOPY), _ret == 1);
+	TEST_RES(do_recv(sk_recv, 0), _ret == 1 && recv_buf[0] == 'd');
+
+	TEST_RES(wait_for_errors(sk_send), _ret);
+
+	TEST_RES(do_recv(sk_send, MSG_ERRQUEUE),
+		 _ret == 0 && (serr = find_extended_err()) &&
+			 serr->ee_origin == SO_EE_ORIGIN_ZEROCOPY &&
+			 serr->ee_info == 2 && serr->ee_data == 2);
+	TEST_ERRNO(do_recv(sk_send, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(set_int_option(sk_send, SO_ZEROCOPY, 0));
+}
+END_TEST()
+
+FN_TEST(tcp_zerocopy)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET, .sin_port = 0 };
+	socklen_t addrlen = sizeof(addr);
+	struct sock_extended_err *serr;
+	int sk_listen, sk_client, sk_server;
+
+	CHECK(inet_aton("127.0.0.1", &addr.sin_addr));
+
+	sk_listen = TEST_SUCC(socket(PF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(bind(sk_listen, (struct sockaddr *)&addr, sizeof(addr)));
+	TEST_SUCC(listen(sk_listen, 1));
+	TEST_SUCC(getsockname(sk_listen, (struct sockaddr *)&addr, &addrlen));
+
+	sk_client = TEST_SUCC(socket(PF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(set_int_option(sk_client, SO_ZEROCOPY, 1));
+	TEST_SUCC(connect(sk_client, (struct sockaddr *)&addr, sizeof(addr)));
+	sk_server = TEST_SUCC(accept(sk_listen, NULL, NULL));
+
+	TEST_ERRNO(do_recv(sk_client, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_RES(send(sk_client, "abc", 3, MSG_ZEROCOPY), _ret == 3);
+	TEST_RES(recv(sk_server, recv_buf, sizeof(recv_buf), 0),
+		 _ret == 3 && memcmp(recv_buf, "abc", 3) == 0);
+
+	TEST_RES(wait_for_errors(sk_client), _ret);
+
+	TEST_RES(do_recv(sk_client, MSG_ERRQUEUE),
+		 _ret == 0 && (recv_msg.msg_flags & MSG_ERRQUEUE) &&
+			 (serr = find_extended_err()) &&
+			 serr->ee_errno == 0 &&
+			 serr->ee_origin == SO_EE_ORIGIN_ZEROCOPY &&
+			 serr->ee_code == SO_EE_CODE_ZEROCOPY_COPIED &&
+			 serr->ee_info == 0 && serr->ee_data == 0);
+	TEST_ERRNO(do_recv(sk_client, MSG_ERRQUEUE), EAGAIN);
+
+	TEST_SUCC(close(sk_server));
+	TEST_SUCC(close(sk_client));
+	TEST_SUCC(close(sk_listen));
+}
+END_TEST()
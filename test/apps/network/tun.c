@@ -0,0 +1,412 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <linux/if_tun.h>
+#include <linux/rtnetlink.h>
+#include <linux/virtio_net.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define TUN_PATH "/dev/net/tun"
+
+#define TUN_ADDR "10.0.5.1"
+#define PEER_ADDR "10.0.5.2"
+#define VNET_TUN_ADDR "10.0.6.1"
+#define VNET_PEER_ADDR "10.0.6.2"
+
+#define LOCAL_PORT 5678
+#define PEER_PORT 1234
+
+#define IP_HLEN 20
+#define UDP_HLEN 8
+
+static int open_tun(void)
+{
+	return open(TUN_PATH, O_RDWR);
+}
+
+static int set_iff(int fd, const char *name, int flags, char *out_name)
+{
+	struct ifreq ifr;
+
+	memset(&ifr, 0, sizeof(ifr));
+	if (name != NULL)
+		strncpy(ifr.ifr_name, name, IFNAMSIZ - 1);
+	ifr.ifr_flags = flags;
+
+	if (ioctl(fd, TUNSETIFF, &ifr) < 0)
+		return -1;
+	if (out_name != NULL)
+		strcpy(out_name, ifr.ifr_name);
+
+	return 0;
+}
+
+static int get_iff_flags(int fd)
+{
+	struct ifreq ifr;
+
+	if (ioctl(fd, TUNGETIFF, &ifr) < 0)
+		return -1;
+
+	return ifr.ifr_flags & 0xffff;
+}
+
+static int set_queue(int fd, int flags)
+{
+	struct ifreq ifr;
+
+	memset(&ifr, 0, sizeof(ifr));
+	ifr.ifr_flags = flags;
+
+	return ioctl(fd, TUNSETQUEUE, &ifr);
+}
+
+// Adds the IPv4 address to the interface with rtnetlink.
+static int add_addr(const char *name, const char *addr, int prefix_len)
+{
+	struct {
+		struct nlmsghdr hdr;
+		struct ifaddrmsg ifa;
+		char attrs[64];
+	} req;
+	struct rtattr *rta;
+	char buffer[4096];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	int sk, err;
+
+	memset(&req, 0, sizeof(req));
+	req.hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifaddrmsg));
+	req.hdr.nlmsg_type = RTM_NEWADDR;
+	req.hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE |
+			      NLM_F_EXCL;
+	req.ifa.ifa_family = AF_INET;
+	req.ifa.ifa_prefixlen = prefix_len;
+	req.ifa.ifa_index = if_nametoindex(name);
+
+	rta = (struct rtattr *)((char *)&req + NLMSG_ALIGN(req.hdr.nlmsg_len));
+	rta->rta_type = IFA_LOCAL;
+	rta->rta_len = RTA_LENGTH(sizeof(struct in_addr));
+	inet_pton(AF_INET, addr, RTA_DATA(rta));
+	req.hdr.nlmsg_len = NLMSG_ALIGN(req.hdr.nlmsg_len) + rta->rta_len;
+
+	sk = socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE);
+	if (sk < 0)
+		return -1;
+	if (send(sk, &req, req.hdr.nlmsg_len, 0) < 0 ||
+	    recv(sk, buffer, sizeof(buffer), 0) < 0 ||
+	    nlh->nlmsg_type != NLMSG_ERROR) {
+		close(sk);
+		return -1;
+	}
+	close(sk);
+
+	err = ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+	if (err < 0) {
+		errno = -err;
+		return -1;
+	}
+
+	return 0;
+}
+
+static int bind_udp(const char *addr)
+{
+	struct sockaddr_in sin;
+	int sk;
+
+	sk = socket(AF_INET, SOCK_DGRAM, 0);
+	if (sk < 0)
+		return -1;
+
+	sin.sin_family = AF_INET;
+	sin.sin_port = htons(LOCAL_PORT);
+	inet_pton(AF_INET, addr, &sin.sin_addr);
+	if (bind(sk, (struct sockaddr *)&sin, sizeof(sin)) < 0) {
+		close(sk);
+		return -1;
+	}
+
+	return sk;
+}
+
+static int send_udp(int sk, const char *addr, const char *msg)
+{
+	struct sockaddr_in sin;
+
+	sin.sin_family = AF_INET;
+	sin.sin_port = htons(PEER_PORT);
+	inet_pton(AF_INET, addr, &sin.sin_addr);
+
+	return sendto(sk, msg, strlen(msg), 0, (struct sockaddr *)&sin,
+		      sizeof(sin));
+}
+
+static unsigned int sum_words(const unsigned char *data, size_t len)
+{
+	unsigned int sum = 0;
+
+	for (size_t i = 0; i + 1 < len; i += 2)
+		sum += (data[i] << 8) | data[i + 1];
+	if (len & 1)
+		sum += data[len - 1] << 8;
+
+	return sum;
+}
+
+static unsigned short fold_sum(unsigned int sum)
+{
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return sum;
+}
+
+// Builds a UDP packet from the peer to the local port. If `partial_csum` is
+// set, the UDP checksum contains only the checksum of the pseudo header, as
+// `VIRTIO_NET_HDR_F_NEEDS_CSUM` requires. Otherwise, the UDP checksum is zero.
+static size_t build_udp(unsigned char *packet, const char *src,
+			const char *dst, const char *msg, int partial_csum)
+{
+	size_t msg_len = strlen(msg);
+	size_t udp_len = UDP_HLEN + msg_len;
+	size_t total_len = IP_HLEN + udp_len;
+	unsigned short csum;
+
+	memset(packet, 0, total_len);
+
+	packet[0] = 0x45;
+	packet[2] = total_len >> 8;
+	packet[3] = total_len & 0xff;
+	packet[8] = 64;
+	packet[9] = IPPROTO_UDP;
+	inet_pton(AF_INET, src, &packet[12]);
+	inet_pton(AF_INET, dst, &packet[16]);
+	csum = ~fold_sum(sum_words(packet, IP_HLEN));
+	packet[10] = csum >> 8;
+	packet[11] = csum & 0xff;
+
+	packet[IP_HLEN + 0] = PEER_PORT >> 8;
+	packet[IP_HLEN + 1] = PEER_PORT & 0xff;
+	packet[IP_HLEN + 2] = LOCAL_PORT >> 8;
+	packet[IP_HLEN + 3] = LOCAL_PORT & 0xff;
+	packet[IP_HLEN + 4] = udp_len >> 8;
+	packet[IP_HLEN + 5] = udp_len & 0xff;
+	memcpy(&packet[IP_HLEN + UDP_HLEN], msg, msg_len);
+
+	if (partial_csum) {
+		csum = fold_sum(sum_words(&packet[12], 8) + IPPROTO_UDP +
+				udp_len);
+		packet[IP_HLEN + 6] = csum >> 8;
+		packet[IP_HLEN + 7] = csum & 0xff;
+	}
+
+	return total_len;
+}
+
+// Checks that the packet is a UDP packet from the local port to the peer with
+// the message.
+static int check_udp(const unsigned char *packet, size_t len, const char *msg)
+{
+	size_t msg_len = strlen(msg);
+
+	return len == IP_HLEN + UDP_HLEN + msg_len &&
+	       (packet[0] >> 4) == 4 && packet[9] == IPPROTO_UDP &&
+	       packet[IP_HLEN + 0] == (LOCAL_PORT >> 8) &&
+	       packet[IP_HLEN + 1] == (LOCAL_PORT & 0xff) &&
+	       memcmp(&packet[IP_HLEN + UDP_HLEN], msg, msg_len) == 0;
+}
+
+FN_TEST(features)
+{
+	int fd = TEST_SUCC(open_tun());
+	unsigned int features;
+
+	TEST_RES(ioctl(fd, TUNGETFEATURES, &features),
+		 (features & IFF_TUN) && (features & IFF_TAP) &&
+			 (features & IFF_NO_PI) && (features & IFF_VNET_HDR) &&
+			 (features & IFF_MULTI_QUEUE));
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(set_iff_error)
+{
+	int fd = TEST_SUCC(open_tun());
+	char buf[64];
+
+	TEST_ERRNO(read(fd, buf, sizeof(buf)), EBADFD);
+	TEST_ERRNO(write(fd, buf, sizeof(buf)), EBADFD);
+	TEST_ERRNO(get_iff_flags(fd), EBADFD);
+
+	TEST_ERRNO(set_iff(fd, "tunerr", 0, NULL), EINVAL);
+	TEST_ERRNO(set_iff(fd, "tunerr", IFF_TUN | IFF_TAP, NULL), EINVAL);
+	TEST_ERRNO(set_iff(fd, "lo", IFF_TUN, NULL), EINVAL);
+	TEST_RES(if_nametoindex("tunerr"), _ret == 0);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(tun_lifecycle)
+{
+	int fd1 = TEST_SUCC(open_tun());
+	int fd2 = TEST_SUCC(open_tun());
+	char name[IFNAMSIZ];
+
+	TEST_RES(set_iff(fd1, NULL, IFF_TUN | IFF_NO_PI, name),
+		 strncmp(name, "tun", 3) == 0);
+	TEST_RES(if_nametoindex(name), _ret > 0);
+	TEST_RES(get_iff_flags(fd1), _ret == (IFF_TUN | IFF_NO_PI));
+	TEST_ERRNO(set_iff(fd1, name, IFF_TUN, NULL), EEXIST);
+
+	// The device has only one queue.
+	TEST_ERRNO(set_iff(fd2, name, IFF_TUN, NULL), EBUSY);
+	TEST_ERRNO(set_iff(fd2, name, IFF_TAP, NULL), EINVAL);
+	TEST_ERRNO(set_iff(fd2, name, IFF_TUN | IFF_TUN_EXCL, NULL), EBUSY);
+
+	// The device is deleted after its last queue is closed.
+	TEST_SUCC(close(fd1));
+	TEST_RES(if_nametoindex(name), _ret == 0);
+
+	// The persistent device is kept.
+	TEST_SUCC(set_iff(fd2, name, IFF_TAP, NULL));
+	TEST_SUCC(ioctl(fd2, TUNSETPERSIST, 1));
+	TEST_RES(get_iff_flags(fd2), _ret == (IFF_TAP | IFF_PERSIST));
+	TEST_SUCC(close(fd2));
+	TEST_RES(if_nametoindex(name), _ret > 0);
+
+	fd2 = TEST_SUCC(open_tun());
+	TEST_SUCC(set_iff(fd2, name, IFF_TAP, NULL));
+	TEST_SUCC(ioctl(fd2, TUNSETPERSIST, 0));
+	TEST_SUCC(close(fd2));
+	TEST_RES(if_nametoindex(name), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(multi_queue)
+{
+	int fd1 = TEST_SUCC(open_tun());
+	int fd2 = TEST_SUCC(open_tun());
+	int fd3 = TEST_SUCC(open_tun());
+	char buf[64];
+
+	TEST_SUCC(set_iff(fd1, "tunmq", IFF_TUN | IFF_MULTI_QUEUE, NULL));
+	TEST_SUCC(set_iff(fd2, "tunmq", IFF_TUN | IFF_MULTI_QUEUE, NULL));
+	TEST_ERRNO(set_iff(fd3, "tunmq", IFF_TUN, NULL), EINVAL);
+
+	TEST_ERRNO(set_queue(fd3, IFF_DETACH_QUEUE), EINVAL);
+	TEST_ERRNO(set_queue(fd2, IFF_ATTACH_QUEUE), EINVAL);
+	TEST_SUCC(set_queue(fd2, IFF_DETACH_QUEUE));
+	TEST_ERRNO(set_queue(fd2, IFF_DETACH_QUEUE), EINVAL);
+	TEST_ERRNO(read(fd2, buf, sizeof(buf)), EBADFD);
+	TEST_ERRNO(get_iff_flags(fd2), EBADFD);
+	TEST_SUCC(set_queue(fd2, IFF_ATTACH_QUEUE));
+	TEST_RES(get_iff_flags(fd2), _ret == (IFF_TUN | IFF_MULTI_QUEUE));
+
+	// The device is kept until all its queues are closed.
+	TEST_SUCC(close(fd1));
+	TEST_RES(if_nametoindex("tunmq"), _ret > 0);
+	TEST_SUCC(close(fd2));
+	TEST_RES(if_nametoindex("tunmq"), _ret == 0);
+
+	TEST_SUCC(close(fd3));
+}
+END_TEST()
+
+FN_TEST(tun_packets)
+{
+	int fd = TEST_SUCC(open_tun());
+	unsigned char packet[256];
+	char buf[64];
+	int sk;
+
+	TEST_SUCC(set_iff(fd, "tunpkt", IFF_TUN | IFF_NO_PI, NULL));
+	TEST_SUCC(fcntl(fd, F_SETFL, O_NONBLOCK));
+	TEST_SUCC(add_addr("tunpkt", TUN_ADDR, 24));
+	sk = TEST_SUCC(bind_udp(TUN_ADDR));
+
+	// Packets sent through the iface are read from the file.
+	TEST_ERRNO(read(fd, packet, sizeof(packet)), EAGAIN);
+	TEST_SUCC(send_udp(sk, PEER_ADDR, "hello"));
+	TEST_RES(read(fd, packet, sizeof(packet)),
+		 check_udp(packet, _ret, "hello"));
+
+	// Packets written to the file are received by the iface.
+	TEST_RES(write(fd, packet,
+		       build_udp(packet, PEER_ADDR, TUN_ADDR, "world", 0)),
+		 _ret == IP_HLEN + UDP_HLEN + 5);
+	TEST_RES(recv(sk, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == 5 && memcmp(buf, "world", 5) == 0);
+
+	// Only IP packets are accepted.
+	memset(packet, 0, sizeof(packet));
+	TEST_ERRNO(write(fd, packet, 40), EINVAL);
+
+	TEST_SUCC(close(sk));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(vnet_hdr)
+{
+	int fd = TEST_SUCC(open_tun());
+	unsigned char packet[256];
+	struct virtio_net_hdr vnet_hdr;
+	char buf[64];
+	int len, sk;
+
+	TEST_SUCC(set_iff(fd, "tunvnet", IFF_TUN | IFF_NO_PI | IFF_VNET_HDR,
+			  NULL));
+	TEST_SUCC(fcntl(fd, F_SETFL, O_NONBLOCK));
+	TEST_SUCC(add_addr("tunvnet", VNET_TUN_ADDR, 24));
+	sk = TEST_SUCC(bind_udp(VNET_TUN_ADDR));
+
+	TEST_RES(ioctl(fd, TUNGETVNETHDRSZ, &len),
+		 len == sizeof(struct virtio_net_hdr));
+	len = 8;
+	TEST_ERRNO(ioctl(fd, TUNSETVNETHDRSZ, &len), EINVAL);
+	len = 12;
+	TEST_SUCC(ioctl(fd, TUNSETVNETHDRSZ, &len));
+
+	TEST_SUCC(ioctl(fd, TUNSETOFFLOAD, TUN_F_CSUM));
+	TEST_ERRNO(ioctl(fd, TUNSETOFFLOAD, TUN_F_CSUM | TUN_F_TSO4), EINVAL);
+	TEST_ERRNO(ioctl(fd, TUNSETOFFLOAD, 0x10000), EINVAL);
+
+	// Packets are preceded by the virtio network header.
+	TEST_SUCC(send_udp(sk, VNET_PEER_ADDR, "hello"));
+	TEST_RES(read(fd, packet, sizeof(packet)),
+		 _ret > 12 && packet[0] == 0 &&
+			 packet[1] == VIRTIO_NET_HDR_GSO_NONE &&
+			 check_udp(packet + 12, _ret - 12, "hello"));
+	TEST_ERRNO(read(fd, packet, 11), EINVAL);
+
+	// The checksum is completed if requested.
+	memset(&vnet_hdr, 0, sizeof(vnet_hdr));
+	vnet_hdr.flags = VIRTIO_NET_HDR_F_NEEDS_CSUM;
+	vnet_hdr.gso_type = VIRTIO_NET_HDR_GSO_NONE;
+	vnet_hdr.csum_start = IP_HLEN;
+	vnet_hdr.csum_offset = 6;
+	memset(packet, 0, 12);
+	memcpy(packet, &vnet_hdr, sizeof(vnet_hdr));
+	len = build_udp(packet + 12, VNET_PEER_ADDR, VNET_TUN_ADDR, "world", 1);
+	TEST_RES(write(fd, packet, 12 + len), _ret == 12 + len);
+	TEST_RES(recv(sk, buf, sizeof(buf), MSG_DONTWAIT),
+		 _ret == 5 && memcmp(buf, "world", 5) == 0);
+
+	// Segmented packets are not supported.
+	vnet_hdr.gso_type = VIRTIO_NET_HDR_GSO_UDP;
+	memcpy(packet, &vnet_hdr, sizeof(vnet_hdr));
+	TEST_ERRNO(write(fd, packet, 12 + len), EINVAL);
+
+	TEST_SUCC(close(sk));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <unistd.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <linux/net_tstamp.h>
+#include <time.h>
+
+#include "test.h"
+
+#define NR_MSGS 3
+
+static int sk_send;
+static int sk_recv;
+
+FN_SETUP(sockets)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET,
+				    .sin_port = htons(0x2345) };
+
+	CHECK(inet_aton("127.0.0.1", &addr.sin_addr));
+
+	sk_recv = CHECK(socket(PF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(sk_recv, (struct sockaddr *)&addr, sizeof(addr)));
+
+	sk_send = CHECK(socket(PF_INET, SOCK_DGRAM, 0));
+	CHECK(connect(sk_send, (struct sockaddr *)&addr, sizeof(addr)));
+}
+END_SETUP()
+
+static char send_bufs[NR_MSGS][16] = { "a", "bb", "ccc" };
+static struct iovec send_iovs[NR_MSGS];
+static struct mmsghdr send_msgs[NR_MSGS];
+
+static char recv_bufs[NR_MSGS + 1][16];
+static struct iovec recv_iovs[NR_MSGS + 1];
+static struct mmsghdr recv_msgs[NR_MSGS + 1];
+
+static void init_msgs(void)
+{
+	int i;
+
+	for (i = 0; i < NR_MSGS; i++) {
+		send_iovs[i].iov_base = send_bufs[i];
+		send_iovs[i].iov_len = i + 1;
+		send_msgs[i].msg_hdr.msg_iov = &send_iovs[i];
+		send_msgs[i].msg_hdr.msg_iovlen = 1;
+		send_msgs[i].msg_hdr.msg_control = NULL;
+		send_msgs[i].msg_hdr.msg_controllen = 0;
+		send_msgs[i].msg_len = 0;
+	}
+
+	for (i = 0; i < NR_MSGS + 1; i++) {
+		recv_iovs[i].iov_base = recv_bufs[i];
+		recv_iovs[i].iov_len = sizeof(recv_bufs[i]);
+		recv_msgs[i].msg_hdr.msg_iov = &recv_iovs[i];
+		recv_msgs[i].msg_hdr.msg_iovlen = 1;
+		recv_msgs[i].msg_hdr.msg_control = NULL;
+		recv_msgs[i].msg_hdr.msg_controllen = 0;
+		recv_msgs[i].msg_len = 0;
+	}
+}
+
+FN_TEST(send_and_recv)
+{
+	init_msgs();
+
+	TEST_RES(sendmmsg(sk_send, send_msgs, 0, 0), _ret == 0);
+	TEST_RES(sendmmsg(sk_send, send_msgs, NR_MSGS, 0),
+		 _ret == NR_MSGS && send_msgs[0].msg_len == 1 &&
+			 send_msgs[1].msg_len == 2 &&
+			 send_msgs[2].msg_len == 3);
+
+	// With `MSG_WAITFORONE`, only the first message is waited for.
+	TEST_RES(recvmmsg(sk_recv, recv_msgs, NR_MSGS + 1, MSG_WAITFORONE,
+			  NULL),
+		 _ret == NR_MSGS && recv_msgs[0].msg_len == 1 &&
+			 recv_msgs[1].msg_len == 2 &&
+			 recv_msgs[2].msg_len == 3 &&
+			 memcmp(recv_bufs[2], "ccc", 3) == 0);
+
+	TEST_ERRNO(recvmmsg(sk_recv, recv_msgs, NR_MSGS, MSG_DONTWAIT, NULL),
+		   EAGAIN);
+}
+END_TEST()
+
+FN_TEST(partial_completion)
+{
+	struct timespec timeout = { .tv_sec = 0, .tv_nsec = 0 };
+
+	init_msgs();
+
+	// The second message has an invalid buffer, so only the first message
+	// is sent.
+	send_iovs[1].iov_base = (void *)1;
+	TEST_RES(sendmmsg(sk_send, send_msgs, NR_MSGS, 0),
+		 _ret == 1 && send_msgs[0].msg_len == 1);
+	TEST_ERRNO(sendmmsg(sk_send, &send_msgs[1], NR_MSGS - 1, 0), EFAULT);
+	TEST_RES(sendmmsg(sk_send, &send_msgs[2], 1, 0), _ret == 1);
+
+	// The timeout is checked after a message is received.
+	TEST_RES(recvmmsg(sk_recv, recv_msgs, NR_MSGS, 0, &timeout),
+		 _ret == 1 && recv_msgs[0].msg_len == 1);
+	TEST_RES(recvmmsg(sk_recv, recv_msgs, NR_MSGS, MSG_WAITFORONE, NULL),
+		 _ret == 1 && recv_msgs[0].msg_len == 3);
+}
+END_TEST()
+
+static char recv_controls[NR_MSGS][64];
+
+static int set_int_option(int sk, int name, int val)
+{
+	return setsockopt(sk, SOL_SOCKET, name, &val, sizeof(val));
+}
+
+// Sends the messages one by one, so that they are received at different times.
+static int send_slowly(void)
+{
+	int i;
+
+	for (i = 0; i < NR_MSGS; i++) {
+		if (send(sk_send, send_bufs[i], i + 1, 0) != i + 1)
+			return -1;
+		usleep(10 * 1000);
+	}
+
+	return 0;
+}
+
+// Returns the `SCM_TIMESTAMPNS` timestamp of the message in nanoseconds, or -1
+// if there is no such timestamp.
+static long long get_timestamp_ns(struct msghdr *msg)
+{
+	struct cmsghdr *cmsg;
+	struct timespec ts;
+
+	for (cmsg = CMSG_FIRSTHDR(msg); cmsg; cmsg = CMSG_NXTHDR(msg, cmsg)) {
+		if (cmsg->cmsg_level != SOL_SOCKET ||
+		    cmsg->cmsg_type != SCM_TIMESTAMPNS ||
+		    cmsg->cmsg_len != CMSG_LEN(sizeof(ts)))
+			continue;
+		memcpy(&ts, CMSG_DATA(cmsg), sizeof(ts));
+		return ts.tv_sec * 1000000000LL + ts.tv_nsec;
+	}
+
+	return -1;
+}
+
+// Checks whether each received message has its own timestamp.
+static int has_timestamps_in_order(void)
+{
+	long long prev = 0, ts;
+	int i;
+
+	for (i = 0; i < NR_MSGS; i++) {
+		ts = get_timestamp_ns(&recv_msgs[i].msg_hdr);
+		if (ts <= prev)
+			return 0;
+		prev = ts;
+	}
+
+	return 1;
+}
+
+FN_TEST(rx_timestamps)
+{
+	int i;
+
+	init_msgs();
+	for (i = 0; i < NR_MSGS; i++) {
+		recv_msgs[i].msg_hdr.msg_control = recv_controls[i];
+		recv_msgs[i].msg_hdr.msg_controllen = sizeof(recv_controls[i]);
+	}
+
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPNS, 1));
+	TEST_SUCC(send_slowly());
+	TEST_RES(recvmmsg(sk_recv, recv_msgs, NR_MSGS, MSG_DONTWAIT, NULL),
+		 _ret == NR_MSGS && recv_msgs[2].msg_len == 3 &&
+			 has_timestamps_in_order());
+	TEST_SUCC(set_int_option(sk_recv, SO_TIMESTAMPNS, 0));
+}
+END_TEST()
+
+static char send_controls[NR_MSGS][CMSG_SPACE(sizeof(__u32))];
+
+// Requests the transmit timestamp of the message via a control message.
+static void request_tx_timestamp(struct msghdr *msg, char *control)
+{
+	__u32 flags = SOF_TIMESTAMPING_TX_SOFTWARE;
+	struct cmsghdr *cmsg;
+
+	memset(control, 0, CMSG_SPACE(sizeof(flags)));
+	msg->msg_control = control;
+	msg->msg_controllen = CMSG_SPACE(sizeof(flags));
+
+	cmsg = CMSG_FIRSTHDR(msg);
+	cmsg->cmsg_level = SOL_SOCKET;
+	cmsg->cmsg_type = SCM_TIMESTAMPING;
+	cmsg->cmsg_len = CMSG_LEN(sizeof(flags));
+	memcpy(CMSG_DATA(cmsg), &flags, sizeof(flags));
+}
+
+// Receives an error from the error queue of `sk_send`.
+static int recv_error(void)
+{
+	struct pollfd pfd = { .fd = sk_send, .events = 0 };
+	char control[256];
+	struct msghdr msg = {
+		.msg_control = control,
+		.msg_controllen = sizeof(control),
+	};
+
+	if (poll(&pfd, 1, 1000) != 1 || !(pfd.revents & POLLERR)) {
+		errno = EAGAIN;
+		return -1;
+	}
+	return recvmsg(sk_send, &msg, MSG_ERRQUEUE | MSG_DONTWAIT);
+}
+
+FN_TEST(tx_timestamps)
+{
+	init_msgs();
+
+	// Only the first and the last messages request transmit timestamps.
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING,
+				 SOF_TIMESTAMPING_SOFTWARE |
+					 SOF_TIMESTAMPING_OPT_TSONLY));
+	request_tx_timestamp(&send_msgs[0].msg_hdr, send_controls[0]);
+	request_tx_timestamp(&send_msgs[2].msg_hdr, send_controls[2]);
+	TEST_RES(sendmmsg(sk_send, send_msgs, NR_MSGS, 0), _ret == NR_MSGS);
+	TEST_RES(recvmmsg(sk_recv, recv_msgs, NR_MSGS, MSG_DONTWAIT, NULL),
+		 _ret == NR_MSGS);
+
+	TEST_RES(recv_error(), _ret == 0);
+	TEST_RES(recv_error(), _ret == 0);
+	TEST_ERRNO(recvmsg(sk_send, &(struct msghdr){ 0 },
+			   MSG_ERRQUEUE | MSG_DONTWAIT),
+		   EAGAIN);
+
+	TEST_SUCC(set_int_option(sk_send, SO_TIMESTAMPING, 0));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(sk_send));
+	CHECK(close(sk_recv));
+}
+END_SETUP()
@@ -0,0 +1,380 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <sys/wait.h>
+#include <fcntl.h>
+#include <unistd.h>
+
+#include "test.h"
+
+static int sk_pair[2];
+
+FN_SETUP(socketpair)
+{
+	CHECK(socketpair(PF_UNIX, SOCK_STREAM, 0, sk_pair));
+}
+END_SETUP()
+
+static int send_fds(int sk, const char *buf, size_t len, int *fds, int nfds)
+{
+	struct iovec iov = { .iov_base = (void *)buf, .iov_len = len };
+	char control[CMSG_SPACE(sizeof(int) * 4)];
+	struct msghdr msg = { .msg_iov = &iov, .msg_iovlen = 1 };
+	struct cmsghdr *cmsg;
+
+	if (nfds > 0) {
+		memset(control, 0, sizeof(control));
+		msg.msg_control = control;
+		msg.msg_controllen = CMSG_SPACE(sizeof(int) * nfds);
+
+		cmsg = CMSG_FIRSTHDR(&msg);
+		cmsg->cmsg_level = SOL_SOCKET;
+		cmsg->cmsg_type = SCM_RIGHTS;
+		cmsg->cmsg_len = CMSG_LEN(sizeof(int) * nfds);
+		memcpy(CMSG_DATA(cmsg), fds, sizeof(int) * nfds);
+	}
+
+	return sendmsg(sk, &msg, 0);
+}
+
+static int send_cred(int sk, const char *buf, size_t len, struct ucred *cred)
+{
+	struct iovec iov = { .iov_base = (void *)buf, .iov_len = len };
+	char control[CMSG_SPACE(sizeof(struct ucred))];
+	struct msghdr msg = {
+		.msg_iov = &iov,
+		.msg_iovlen = 1,
+		.msg_control = control,
+		.msg_controllen = sizeof(control),
+	};
+	struct cmsghdr *cmsg;
+
+	memset(control, 0, sizeof(control));
+	cmsg = CMSG_FIRSTHDR(&msg);
+	cmsg->cmsg_level = SOL_SOCKET;
+	cmsg->cmsg_type = SCM_CREDENTIALS;
+	cmsg->cmsg_len = CMSG_LEN(sizeof(struct ucred));
+	memcpy(CMSG_DATA(cmsg), cred, sizeof(struct ucred));
+
+	return sendmsg(sk, &msg, 0);
+}
+
+static char recv_buf[16];
+static char recv_control[CMSG_SPACE(sizeof(int) * 4) +
+			 CMSG_SPACE(sizeof(struct ucred))];
+static struct msghdr recv_msg;
+
+static int do_recv(int sk, size_t controllen, int flags)
+{
+	static struct iovec iov;
+
+	memset(recv_buf, 0, sizeof(recv_buf));
+	memset(recv_control, 0, sizeof(recv_control));
+
+	iov.iov_base = recv_buf;
+	iov.iov_len = sizeof(recv_buf);
+
+	memset(&recv_msg, 0, sizeof(recv_msg));
+	recv_msg.msg_iov = &iov;
+	recv_msg.msg_iovlen = 1;
+	recv_msg.msg_control = controllen ? recv_control : NULL;
+	recv_msg.msg_controllen = controllen;
+
+	return recvmsg(sk, &recv_msg, flags);
+}
+
+static struct cmsghdr *find_cmsg(int type)
+{
+	struct cmsghdr *cmsg;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg != NULL;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg))
+		if (cmsg->cmsg_level == SOL_SOCKET && cmsg->cmsg_type == type)
+			return cmsg;
+
+	return NULL;
+}
+
+static int received_fd(int index)
+{
+	struct cmsghdr *cmsg = find_cmsg(SCM_RIGHTS);
+	int fd;
+
+	if (cmsg == NULL ||
+	    cmsg->cmsg_len < CMSG_LEN(sizeof(int) * (index + 1)))
+		return -1;
+
+	memcpy(&fd, CMSG_DATA(cmsg) + sizeof(int) * index, sizeof(int));
+	return fd;
+}
+
+static int received_cred(struct ucred *cred)
+{
+	struct cmsghdr *cmsg = find_cmsg(SCM_CREDENTIALS);
+
+	if (cmsg == NULL || cmsg->cmsg_len != CMSG_LEN(sizeof(struct ucred)))
+		return -1;
+
+	memcpy(cred, CMSG_DATA(cmsg), sizeof(struct ucred));
+	return 0;
+}
+
+FN_TEST(pass_fds)
+{
+	int pipe_fds[2];
+	int fd;
+	char buf[6];
+
+	TEST_SUCC(pipe(pipe_fds));
+
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, pipe_fds, 2), _ret == 6);
+	TEST_SUCC(close(pipe_fds[0]));
+
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 6 && strcmp(recv_buf, "hello") == 0 &&
+			 recv_msg.msg_flags == 0 &&
+			 recv_msg.msg_controllen ==
+				 CMSG_SPACE(sizeof(int) * 2) &&
+			 received_fd(0) >= 0 && received_fd(1) >= 0);
+
+	// The received read end should be connected to the original write end.
+	fd = received_fd(0);
+	TEST_RES(write(pipe_fds[1], "world", 6), _ret == 6);
+	TEST_RES(read(fd, buf, sizeof(buf)),
+		 _ret == 6 && strcmp(buf, "world") == 0);
+
+	TEST_RES(fcntl(fd, F_GETFD), _ret == 0);
+
+	TEST_SUCC(close(fd));
+	TEST_SUCC(close(received_fd(1)));
+	TEST_SUCC(close(pipe_fds[1]));
+}
+END_TEST()
+
+FN_TEST(pass_fds_cloexec)
+{
+	int fd = sk_pair[0];
+
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, &fd, 1), _ret == 6);
+
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), MSG_CMSG_CLOEXEC),
+		 _ret == 6 && received_fd(0) >= 0);
+
+	fd = received_fd(0);
+	TEST_RES(fcntl(fd, F_GETFD), _ret == FD_CLOEXEC);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(pass_fds_boundary)
+{
+	int fds[2] = { sk_pair[0], sk_pair[1] };
+
+	// The bytes carrying files are not merged with the following bytes.
+	TEST_RES(send_fds(sk_pair[0], "abc", 3, fds, 1), _ret == 3);
+	TEST_RES(send_fds(sk_pair[0], "def", 3, NULL, 0), _ret == 3);
+
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 3 && memcmp(recv_buf, "abc", 3) == 0 &&
+			 received_fd(0) >= 0);
+	TEST_SUCC(close(received_fd(0)));
+
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 3 && memcmp(recv_buf, "def", 3) == 0 &&
+			 recv_msg.msg_controllen == 0);
+
+	// The bytes carrying files are not merged with the preceding bytes either.
+	TEST_RES(send_fds(sk_pair[0], "abc", 3, NULL, 0), _ret == 3);
+	TEST_RES(send_fds(sk_pair[0], "def", 3, fds, 2), _ret == 3);
+
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 6 && memcmp(recv_buf, "abcdef", 6) == 0 &&
+			 received_fd(0) >= 0 && received_fd(1) >= 0);
+	TEST_SUCC(close(received_fd(0)));
+	TEST_SUCC(close(received_fd(1)));
+}
+END_TEST()
+
+FN_TEST(pass_fds_truncated)
+{
+	int fds[2] = { sk_pair[0], sk_pair[1] };
+
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, fds, 2), _ret == 6);
+	TEST_RES(do_recv(sk_pair[1], CMSG_LEN(sizeof(int)), 0),
+		 _ret == 6 && recv_msg.msg_flags == MSG_CTRUNC &&
+			 recv_msg.msg_controllen == CMSG_LEN(sizeof(int)) &&
+			 received_fd(0) >= 0 && received_fd(1) < 0);
+	TEST_SUCC(close(received_fd(0)));
+
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, fds, 2), _ret == 6);
+	TEST_RES(do_recv(sk_pair[1], 0, 0),
+		 _ret == 6 && recv_msg.msg_flags == MSG_CTRUNC &&
+			 recv_msg.msg_controllen == 0);
+}
+END_TEST()
+
+FN_TEST(pass_fds_invalid)
+{
+	int fd = 1000;
+
+	TEST_ERRNO(send_fds(sk_pair[0], "hello", 6, &fd, 1), EBADF);
+}
+END_TEST()
+
+FN_TEST(pass_fds_cycle)
+{
+	int sks[2];
+
+	// Send each socket through itself and close both. The sockets can only be
+	// reclaimed by the garbage collector.
+	TEST_SUCC(socketpair(PF_UNIX, SOCK_STREAM, 0, sks));
+	TEST_RES(send_fds(sks[0], "a", 1, &sks[0], 1), _ret == 1);
+	TEST_RES(send_fds(sks[1], "b", 1, &sks[1], 1), _ret == 1);
+	TEST_SUCC(close(sks[0]));
+	TEST_SUCC(close(sks[1]));
+
+	// Passing more files triggers the garbage collection.
+	TEST_SUCC(socketpair(PF_UNIX, SOCK_STREAM, 0, sks));
+	TEST_RES(send_fds(sks[0], "a", 1, &sks[1], 1), _ret == 1);
+	TEST_SUCC(close(sks[0]));
+	TEST_SUCC(close(sks[1]));
+}
+END_TEST()
+
+FN_TEST(pass_cred)
+{
+	int enable = 1;
+	int optval;
+	socklen_t optlen = sizeof(optval);
+	struct ucred cred;
+
+	TEST_RES(getsockopt(sk_pair[1], SOL_SOCKET, SO_PASSCRED, &optval,
+			    &optlen),
+		 optlen == sizeof(optval) && optval == 0);
+
+	TEST_SUCC(setsockopt(sk_pair[1], SOL_SOCKET, SO_PASSCRED, &enable,
+			     sizeof(enable)));
+
+	TEST_RES(getsockopt(sk_pair[1], SOL_SOCKET, SO_PASSCRED, &optval,
+			    &optlen),
+		 optlen == sizeof(optval) && optval == 1);
+
+	// The default credentials are attached.
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, NULL, 0), _ret == 6);
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 6 && received_cred(&cred) == 0 &&
+			 cred.pid == getpid() && cred.uid == getuid() &&
+			 cred.gid == getgid());
+
+	// The explicit credentials are attached.
+	cred.pid = getpid();
+	cred.uid = getuid();
+	cred.gid = getgid();
+	TEST_RES(send_cred(sk_pair[0], "world", 6, &cred), _ret == 6);
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 6 && strcmp(recv_buf, "world") == 0 &&
+			 received_cred(&cred) == 0 && cred.pid == getpid());
+
+	enable = 0;
+	TEST_SUCC(setsockopt(sk_pair[1], SOL_SOCKET, SO_PASSCRED, &enable,
+			     sizeof(enable)));
+
+	TEST_RES(send_fds(sk_pair[0], "hello", 6, NULL, 0), _ret == 6);
+	TEST_RES(do_recv(sk_pair[1], sizeof(recv_control), 0),
+		 _ret == 6 && recv_msg.msg_controllen == 0);
+}
+END_TEST()
+
+FN_TEST(pass_cred_forged)
+{
+	int pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		struct ucred cred = { .pid = getpid(), .uid = 0, .gid = 0 };
+
+		// Drop the privileges, so that no credentials can be forged.
+		CHECK(setresgid(65534, 65534, 65534));
+		CHECK(setresuid(65534, 65534, 65534));
+
+		if (send_cred(sk_pair[0], "hello", 6, &cred) >= 0 ||
+		    errno != EPERM)
+			exit(EXIT_FAILURE);
+
+		cred.pid = 1;
+		cred.uid = 65534;
+		cred.gid = 65534;
+		if (send_cred(sk_pair[0], "hello", 6, &cred) >= 0 ||
+		    errno != EPERM)
+			exit(EXIT_FAILURE);
+
+		exit(EXIT_SUCCESS);
+	}
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_TEST(peer_cred)
+{
+	struct ucred cred;
+	socklen_t optlen = sizeof(cred);
+	int sk;
+
+	TEST_RES(getsockopt(sk_pair[0], SOL_SOCKET, SO_PEERCRED, &cred,
+			    &optlen),
+		 optlen == sizeof(cred) && cred.pid == getpid() &&
+			 cred.uid == geteuid() && cred.gid == getegid());
+
+	sk = TEST_SUCC(socket(PF_UNIX, SOCK_STREAM, 0));
+	TEST_RES(getsockopt(sk, SOL_SOCKET, SO_PEERCRED, &cred, &optlen),
+		 optlen == sizeof(cred) && cred.pid == 0 &&
+			 cred.uid == (uid_t)-1 && cred.gid == (gid_t)-1);
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(peer_cred_connected)
+{
+	struct sockaddr_un addr = { .sun_family = AF_UNIX,
+				    .sun_path = "\0unix_scm" };
+	socklen_t addrlen = sizeof(addr.sun_family) + 9;
+	struct ucred cred;
+	socklen_t optlen = sizeof(cred);
+	int sk_listen, sk_connect, sk_accept;
+	int enable = 1;
+	int optval;
+
+	sk_listen = TEST_SUCC(socket(PF_UNIX, SOCK_STREAM, 0));
+	TEST_SUCC(bind(sk_listen, (struct sockaddr *)&addr, addrlen));
+	TEST_SUCC(listen(sk_listen, 1));
+	TEST_SUCC(setsockopt(sk_listen, SOL_SOCKET, SO_PASSCRED, &enable,
+			     sizeof(enable)));
+
+	sk_connect = TEST_SUCC(socket(PF_UNIX, SOCK_STREAM, 0));
+	TEST_SUCC(connect(sk_connect, (struct sockaddr *)&addr, addrlen));
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+
+	TEST_RES(getsockopt(sk_connect, SOL_SOCKET, SO_PEERCRED, &cred,
+			    &optlen),
+		 optlen == sizeof(cred) && cred.pid == getpid());
+	TEST_RES(getsockopt(sk_accept, SOL_SOCKET, SO_PEERCRED, &cred,
+			    &optlen),
+		 optlen == sizeof(cred) && cred.pid == getpid());
+
+	// The accepted socket inherits `SO_PASSCRED` from the listening socket.
+	optlen = sizeof(optval);
+	TEST_RES(getsockopt(sk_accept, SOL_SOCKET, SO_PASSCRED, &optval,
+			    &optlen),
+		 optlen == sizeof(optval) && optval == 1);
+
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+	TEST_SUCC(close(sk_listen));
+}
+END_TEST()
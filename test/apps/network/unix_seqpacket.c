@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <unistd.h>
+#include <stddef.h>
+
+#include "test.h"
+
+#define PATH_OFFSET offsetof(struct sockaddr_un, sun_path)
+
+#define LISTEN_NAME "\0seqpacket"
+#define LISTEN_ADDRLEN (PATH_OFFSET + sizeof(LISTEN_NAME) - 1)
+
+// The length of an autobind address, which is a NUL byte followed by five
+// hexadecimal digits.
+#define AUTOBIND_ADDRLEN (PATH_OFFSET + 6)
+
+static struct sockaddr_un listen_addr = {
+	.sun_family = AF_UNIX,
+	.sun_path = LISTEN_NAME,
+};
+
+static int sk_pair[2];
+static int sk_listen;
+
+static char big_buf[1 << 20];
+
+FN_SETUP(socketpair)
+{
+	CHECK(socketpair(PF_UNIX, SOCK_SEQPACKET | SOCK_NONBLOCK, 0, sk_pair));
+}
+END_SETUP()
+
+FN_SETUP(listen)
+{
+	sk_listen = CHECK(socket(PF_UNIX, SOCK_SEQPACKET, 0));
+	CHECK(bind(sk_listen, (struct sockaddr *)&listen_addr,
+		   LISTEN_ADDRLEN));
+	CHECK(listen(sk_listen, 4));
+}
+END_SETUP()
+
+static int recv_flags(int sk, char *buf, size_t len, int flags, int *msg_flags)
+{
+	struct iovec iov = { .iov_base = buf, .iov_len = len };
+	struct msghdr msg = { .msg_iov = &iov, .msg_iovlen = 1 };
+	int ret;
+
+	ret = recvmsg(sk, &msg, flags);
+	*msg_flags = msg.msg_flags;
+
+	return ret;
+}
+
+FN_TEST(record_boundaries)
+{
+	char buf[16];
+
+	TEST_RES(send(sk_pair[0], "hello", 5, 0), _ret == 5);
+	TEST_RES(send(sk_pair[0], "world", 5, 0), _ret == 5);
+
+	// Each record is received separately, even if the buffer is large
+	// enough to hold both of them.
+	TEST_RES(recv(sk_pair[1], buf, sizeof(buf), 0),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+	TEST_RES(recv(sk_pair[1], buf, sizeof(buf), 0),
+		 _ret == 5 && memcmp(buf, "world", 5) == 0);
+	TEST_ERRNO(recv(sk_pair[1], buf, sizeof(buf), 0), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(record_truncation)
+{
+	char buf[16];
+	int msg_flags;
+
+	// The rest of a truncated record is discarded.
+	TEST_RES(send(sk_pair[0], "hello world", 11, 0), _ret == 11);
+	TEST_RES(send(sk_pair[0], "abc", 3, 0), _ret == 3);
+	TEST_RES(recv_flags(sk_pair[1], buf, 5, 0, &msg_flags),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0 &&
+			 msg_flags == MSG_TRUNC);
+	TEST_RES(recv_flags(sk_pair[1], buf, sizeof(buf), 0, &msg_flags),
+		 _ret == 3 && memcmp(buf, "abc", 3) == 0 && msg_flags == 0);
+
+	// With `MSG_TRUNC`, the real length of the record is returned.
+	TEST_RES(send(sk_pair[0], "hello world", 11, 0), _ret == 11);
+	TEST_RES(recv_flags(sk_pair[1], buf, 5, MSG_TRUNC, &msg_flags),
+		 _ret == 11 && memcmp(buf, "hello", 5) == 0 &&
+			 msg_flags == MSG_TRUNC);
+	TEST_ERRNO(recv(sk_pair[1], buf, sizeof(buf), 0), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(record_too_large)
+{
+	char buf[16];
+
+	// A record is never partially sent.
+	TEST_ERRNO(send(sk_pair[0], big_buf, sizeof(big_buf), 0), EMSGSIZE);
+	TEST_ERRNO(recv(sk_pair[1], buf, sizeof(buf), 0), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(abstract_connect)
+{
+	int sk_connect, sk_accept;
+	struct sockaddr_un addr;
+	socklen_t addrlen;
+	char buf[16];
+
+	sk_connect = TEST_SUCC(socket(PF_UNIX, SOCK_SEQPACKET, 0));
+	TEST_SUCC(connect(sk_connect, (struct sockaddr *)&listen_addr,
+			  LISTEN_ADDRLEN));
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+
+	// The accepted socket inherits the address of the listening socket.
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk_accept, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == LISTEN_ADDRLEN &&
+			 memcmp(&addr, &listen_addr, LISTEN_ADDRLEN) == 0);
+	addrlen = sizeof(addr);
+	TEST_RES(getpeername(sk_connect, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == LISTEN_ADDRLEN &&
+			 memcmp(&addr, &listen_addr, LISTEN_ADDRLEN) == 0);
+
+	// The accepted socket also preserves record boundaries.
+	TEST_RES(send(sk_connect, "hello", 5, 0), _ret == 5);
+	TEST_RES(send(sk_connect, "world", 5, 0), _ret == 5);
+	TEST_RES(recv(sk_accept, buf, sizeof(buf), 0),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+	TEST_RES(recv(sk_accept, buf, sizeof(buf), 0),
+		 _ret == 5 && memcmp(buf, "world", 5) == 0);
+
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+}
+END_TEST()
+
+FN_TEST(connect_type_mismatch)
+{
+	int sk_connect;
+
+	sk_connect = TEST_SUCC(socket(PF_UNIX, SOCK_STREAM, 0));
+	TEST_ERRNO(connect(sk_connect, (struct sockaddr *)&listen_addr,
+			   LISTEN_ADDRLEN),
+		   EPROTOTYPE);
+	TEST_SUCC(close(sk_connect));
+}
+END_TEST()
+
+FN_TEST(autobind_on_connect)
+{
+	int sk_connect, sk_accept, one = 1;
+	struct sockaddr_un addr, peer_addr;
+	socklen_t addrlen;
+
+	// Without `SO_PASSCRED`, the connecting socket stays unnamed.
+	sk_connect = TEST_SUCC(socket(PF_UNIX, SOCK_SEQPACKET, 0));
+	TEST_SUCC(connect(sk_connect, (struct sockaddr *)&listen_addr,
+			  LISTEN_ADDRLEN));
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk_connect, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == PATH_OFFSET);
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+
+	// With `SO_PASSCRED`, the connecting socket is autobound.
+	sk_connect = TEST_SUCC(socket(PF_UNIX, SOCK_SEQPACKET, 0));
+	TEST_SUCC(setsockopt(sk_connect, SOL_SOCKET, SO_PASSCRED, &one,
+			     sizeof(one)));
+	TEST_SUCC(connect(sk_connect, (struct sockaddr *)&listen_addr,
+			  LISTEN_ADDRLEN));
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk_connect, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == AUTOBIND_ADDRLEN && addr.sun_path[0] == '\0');
+
+	// The peer sees the autobind address.
+	sk_accept = TEST_SUCC(accept(sk_listen, NULL, NULL));
+	addrlen = sizeof(peer_addr);
+	TEST_RES(getpeername(sk_accept, (struct sockaddr *)&peer_addr,
+			     &addrlen),
+		 addrlen == AUTOBIND_ADDRLEN &&
+			 memcmp(&peer_addr, &addr, AUTOBIND_ADDRLEN) == 0);
+
+	TEST_SUCC(close(sk_accept));
+	TEST_SUCC(close(sk_connect));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(sk_listen));
+	CHECK(close(sk_pair[0]));
+	CHECK(close(sk_pair[1]));
+}
+END_SETUP()
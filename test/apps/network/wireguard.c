@@ -0,0 +1,363 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <arpa/inet.h>
+#include <linux/genetlink.h>
+#include <linux/if_link.h>
+#include <linux/rtnetlink.h>
+#include <linux/wireguard.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define LOOPBACK_NAME "lo"
+#define WG_NAME "wg0"
+#define WG_PORT 51820
+
+#define BUFFER_SIZE 8192
+
+static int rtnl_sk;
+static int genl_sk;
+static int wg_family;
+
+FN_SETUP(netlink_sk)
+{
+	rtnl_sk = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	genl_sk = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_GENERIC));
+}
+END_SETUP()
+
+struct nl_req {
+	struct nlmsghdr hdr;
+	union {
+		struct ifinfomsg ifi;
+		struct genlmsghdr genl;
+	};
+	char attrs[512];
+};
+
+static struct nlattr *add_attr(struct nl_req *req, unsigned short type,
+			       const void *data, size_t len)
+{
+	struct nlattr *nla =
+		(struct nlattr *)((char *)req + NLMSG_ALIGN(req->hdr.nlmsg_len));
+
+	nla->nla_type = type;
+	nla->nla_len = NLA_HDRLEN + len;
+	if (len != 0)
+		memcpy((char *)nla + NLA_HDRLEN, data, len);
+
+	req->hdr.nlmsg_len =
+		NLMSG_ALIGN(req->hdr.nlmsg_len) + NLA_ALIGN(nla->nla_len);
+
+	return nla;
+}
+
+static struct nlattr *begin_nested(struct nl_req *req, unsigned short type)
+{
+	return add_attr(req, type | NLA_F_NESTED, NULL, 0);
+}
+
+static void end_nested(struct nl_req *req, struct nlattr *nested)
+{
+	nested->nla_len = (char *)req + req->hdr.nlmsg_len - (char *)nested;
+}
+
+static void init_link_req(struct nl_req *req, int type, int flags,
+			  const char *name)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(sizeof(struct ifinfomsg));
+	req->hdr.nlmsg_type = type;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+	req->ifi.ifi_family = AF_UNSPEC;
+
+	add_attr(req, IFLA_IFNAME, name, strlen(name) + 1);
+}
+
+static void init_genl_req(struct nl_req *req, int family, int cmd, int flags)
+{
+	memset(req, 0, sizeof(*req));
+	req->hdr.nlmsg_len = NLMSG_LENGTH(GENL_HDRLEN);
+	req->hdr.nlmsg_type = family;
+	req->hdr.nlmsg_flags = NLM_F_REQUEST | flags;
+	req->genl.cmd = cmd;
+	req->genl.version = 1;
+}
+
+// Sends the request and returns the error code in the acknowledgment.
+static int nl_ack(int sk, struct nl_req *req)
+{
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+
+	if (send(sk, req, req->hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != NLMSG_ERROR)
+		return -1;
+
+	return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+}
+
+#define for_each_attr(nla, data, len)                                     \
+	for (nla = (struct nlattr *)(data); (len) >= NLA_HDRLEN &&        \
+					    nla->nla_len >= NLA_HDRLEN && \
+					    nla->nla_len <= (len);        \
+	     (len) -= NLA_ALIGN(nla->nla_len),                            \
+	    nla = (struct nlattr *)((char *)nla + NLA_ALIGN(nla->nla_len)))
+
+#define attr_data(nla) ((void *)((char *)(nla) + NLA_HDRLEN))
+#define attr_len(nla) ((int)(nla)->nla_len - NLA_HDRLEN)
+#define attr_type(nla) ((nla)->nla_type & NLA_TYPE_MASK)
+
+// Returns the ID of the family, or the negative error code.
+static int get_family(const char *name)
+{
+	struct nl_req req;
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct nlattr *nla;
+	int len;
+	int id = -1;
+
+	init_genl_req(&req, GENL_ID_CTRL, CTRL_CMD_GETFAMILY, 0);
+	add_attr(&req, CTRL_ATTR_FAMILY_NAME, name, strlen(name) + 1);
+
+	if (send(genl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(genl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type == NLMSG_ERROR)
+		return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+	if (nlh->nlmsg_type != GENL_ID_CTRL)
+		return -1;
+
+	len = nlh->nlmsg_len - NLMSG_LENGTH(GENL_HDRLEN);
+	for_each_attr(nla, (char *)NLMSG_DATA(nlh) + GENL_HDRLEN, len)
+	{
+		if (attr_type(nla) == CTRL_ATTR_FAMILY_ID)
+			id = *(__u16 *)attr_data(nla);
+	}
+
+	return id;
+}
+
+static const __u8 private_key[WG_KEY_LEN] = {
+	0x48, 0x2e, 0x3b, 0x5c, 0x1f, 0x7a, 0x92, 0x03, 0xd4, 0x65, 0xa7,
+	0x18, 0xc9, 0x2a, 0xbb, 0x0c, 0x6d, 0xfe, 0x3f, 0x80, 0x51, 0xe2,
+	0x13, 0xa4, 0x75, 0x06, 0x97, 0x28, 0xb9, 0x4a, 0xdb, 0x6c,
+};
+
+static const __u8 peer_public_key[WG_KEY_LEN] = {
+	0x9a, 0x2b, 0xcc, 0x1d, 0x7e, 0x3f, 0x50, 0xa1, 0x42, 0xe3, 0x14,
+	0x85, 0x26, 0xc7, 0x68, 0x09, 0xfa, 0x6b, 0x1c, 0x8d, 0x3e, 0xaf,
+	0x40, 0xd1, 0x72, 0x13, 0xb4, 0x25, 0xe6, 0x57, 0x88, 0x19,
+};
+
+static void init_wg_req(struct nl_req *req, int cmd, int flags,
+			const char *name)
+{
+	init_genl_req(req, wg_family, cmd, flags);
+	if (name != NULL)
+		add_attr(req, WGDEVICE_A_IFNAME, name, strlen(name) + 1);
+}
+
+static void add_peer(struct nl_req *req, const char *allowed_ip,
+		     __u8 cidr_mask)
+{
+	struct nlattr *peers, *peer, *allowed_ips, *ip;
+	struct sockaddr_in endpoint = {
+		.sin_family = AF_INET,
+		.sin_port = htons(WG_PORT),
+		.sin_addr = { htonl(INADDR_LOOPBACK) },
+	};
+	__u16 family = AF_INET;
+	struct in_addr addr;
+
+	inet_pton(AF_INET, allowed_ip, &addr);
+
+	peers = begin_nested(req, WGDEVICE_A_PEERS);
+	peer = begin_nested(req, 0);
+	add_attr(req, WGPEER_A_PUBLIC_KEY, peer_public_key, WG_KEY_LEN);
+	add_attr(req, WGPEER_A_ENDPOINT, &endpoint, sizeof(endpoint));
+	allowed_ips = begin_nested(req, WGPEER_A_ALLOWEDIPS);
+	ip = begin_nested(req, 0);
+	add_attr(req, WGALLOWEDIP_A_FAMILY, &family, sizeof(family));
+	add_attr(req, WGALLOWEDIP_A_IPADDR, &addr, sizeof(addr));
+	add_attr(req, WGALLOWEDIP_A_CIDR_MASK, &cidr_mask, sizeof(cidr_mask));
+	end_nested(req, ip);
+	end_nested(req, allowed_ips);
+	end_nested(req, peer);
+	end_nested(req, peers);
+}
+
+struct wg_device {
+	int listen_port;
+	int has_private_key;
+	int num_peers;
+	int num_allowed_ips;
+};
+
+// Dumps the device and returns 0 on success, or the negative error code.
+static int get_device(const char *name, struct wg_device *device)
+{
+	struct nl_req req;
+	char buffer[BUFFER_SIZE];
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buffer;
+	struct nlattr *nla, *peer, *peer_nla, *ip;
+	int len, peers_len, peer_len, ips_len;
+
+	memset(device, 0, sizeof(*device));
+
+	init_wg_req(&req, WG_CMD_GET_DEVICE, NLM_F_DUMP, name);
+
+	if (send(genl_sk, &req, req.hdr.nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(genl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type == NLMSG_ERROR)
+		return ((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+	if (nlh->nlmsg_type != wg_family)
+		return -1;
+
+	len = nlh->nlmsg_len - NLMSG_LENGTH(GENL_HDRLEN);
+	for_each_attr(nla, (char *)NLMSG_DATA(nlh) + GENL_HDRLEN, len)
+	{
+		switch (attr_type(nla)) {
+		case WGDEVICE_A_LISTEN_PORT:
+			device->listen_port = *(__u16 *)attr_data(nla);
+			break;
+		case WGDEVICE_A_PRIVATE_KEY:
+			device->has_private_key =
+				attr_len(nla) == WG_KEY_LEN &&
+				memcmp(attr_data(nla), private_key,
+				       WG_KEY_LEN) == 0;
+			break;
+		case WGDEVICE_A_PEERS:
+			peers_len = attr_len(nla);
+			for_each_attr(peer, attr_data(nla), peers_len)
+			{
+				device->num_peers++;
+				peer_len = attr_len(peer);
+				for_each_attr(peer_nla, attr_data(peer),
+					      peer_len)
+				{
+					if (attr_type(peer_nla) !=
+					    WGPEER_A_ALLOWEDIPS)
+						continue;
+					ips_len = attr_len(peer_nla);
+					for_each_attr(ip, attr_data(peer_nla),
+						      ips_len)
+						device->num_allowed_ips++;
+				}
+			}
+			break;
+		}
+	}
+
+	// Skip the done segment.
+	if (recv(genl_sk, buffer, sizeof(buffer), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != NLMSG_DONE)
+		return -1;
+
+	return 0;
+}
+
+FN_TEST(new_wireguard)
+{
+	struct nl_req req;
+	struct nlattr *link_info;
+
+	init_link_req(&req, RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, WG_NAME);
+	link_info = begin_nested(&req, IFLA_LINKINFO);
+	add_attr(&req, IFLA_INFO_KIND, "wireguard", strlen("wireguard"));
+	end_nested(&req, link_info);
+	TEST_RES(nl_ack(rtnl_sk, &req), _ret == 0);
+	TEST_RES(nl_ack(rtnl_sk, &req), _ret == -EEXIST);
+
+	TEST_RES(if_nametoindex(WG_NAME), _ret > 0);
+}
+END_TEST()
+
+FN_TEST(get_family)
+{
+	wg_family = TEST_RES(get_family(WG_GENL_NAME), _ret > 0);
+	TEST_RES(get_family("nlctrl"), _ret == GENL_ID_CTRL);
+	TEST_RES(get_family("nonexistent"), _ret == -ENOENT);
+}
+END_TEST()
+
+FN_TEST(set_device)
+{
+	struct nl_req req;
+	struct wg_device device;
+	__u16 port = WG_PORT;
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, WG_NAME);
+	add_attr(&req, WGDEVICE_A_PRIVATE_KEY, private_key, WG_KEY_LEN);
+	add_attr(&req, WGDEVICE_A_LISTEN_PORT, &port, sizeof(port));
+	add_peer(&req, "10.7.0.2", 32);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == 0);
+
+	TEST_RES(get_device(WG_NAME, &device),
+		 _ret == 0 && device.listen_port == WG_PORT &&
+			 device.has_private_key && device.num_peers == 1 &&
+			 device.num_allowed_ips == 1);
+
+	// Adding the same peer again merges the allowed IPs.
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, WG_NAME);
+	add_peer(&req, "10.7.1.0", 24);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == 0);
+
+	TEST_RES(get_device(WG_NAME, &device),
+		 _ret == 0 && device.num_peers == 1 &&
+			 device.num_allowed_ips == 2);
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, WG_NAME);
+	add_peer(&req, "10.7.2.0", 33);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -EINVAL);
+}
+END_TEST()
+
+FN_TEST(device_error)
+{
+	struct nl_req req;
+	struct wg_device device;
+	__u32 index = if_nametoindex(WG_NAME);
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, NULL);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -EBADR);
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, WG_NAME);
+	add_attr(&req, WGDEVICE_A_IFINDEX, &index, sizeof(index));
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -EBADR);
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, "wg1");
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -ENODEV);
+
+	init_wg_req(&req, WG_CMD_SET_DEVICE, NLM_F_ACK, LOOPBACK_NAME);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -EOPNOTSUPP);
+
+	init_wg_req(&req, WG_CMD_GET_DEVICE, NLM_F_ACK, WG_NAME);
+	TEST_RES(nl_ack(genl_sk, &req), _ret == -EOPNOTSUPP);
+
+	TEST_RES(get_device("wg1", &device), _ret == -ENODEV);
+}
+END_TEST()
+
+FN_TEST(del_wireguard)
+{
+	struct nl_req req;
+
+	init_link_req(&req, RTM_DELLINK, 0, WG_NAME);
+	TEST_RES(nl_ack(rtnl_sk, &req), _ret == 0);
+	TEST_RES(nl_ack(rtnl_sk, &req), _ret == -ENODEV);
+
+	TEST_RES(if_nametoindex(WG_NAME), _ret == 0);
+}
+END_TEST()
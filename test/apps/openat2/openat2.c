@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <linux/openat2.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+
+#include "../network/test.h"
+
+#define BASE_DIR "/tmp/openat2_test"
+
+static int base_fd;
+static ino_t file_ino;
+
+static int sys_openat2(int dirfd, const char *path, __u64 flags, __u64 mode,
+		       __u64 resolve)
+{
+	struct open_how how = {
+		.flags = flags,
+		.mode = mode,
+		.resolve = resolve,
+	};
+
+	return syscall(SYS_openat2, dirfd, path, &how, sizeof(how));
+}
+
+static int is_file(int fd)
+{
+	struct stat stat_buf;
+
+	if (fstat(fd, &stat_buf) < 0) {
+		return 0;
+	}
+	close(fd);
+
+	return stat_buf.st_ino == file_ino;
+}
+
+FN_SETUP(dirs)
+{
+	struct stat stat_buf;
+
+	CHECK(mkdir(BASE_DIR, 0755));
+	CHECK(mkdir(BASE_DIR "/sub", 0755));
+	CHECK(close(CHECK(open(BASE_DIR "/file", O_CREAT | O_WRONLY, 0644))));
+	CHECK(symlink("/tmp", BASE_DIR "/link_abs"));
+	CHECK(symlink("../..", BASE_DIR "/link_up"));
+	CHECK(symlink("../file", BASE_DIR "/sub/link_file"));
+
+	CHECK(stat(BASE_DIR "/file", &stat_buf));
+	file_ino = stat_buf.st_ino;
+	base_fd = CHECK(open(BASE_DIR, O_RDONLY | O_DIRECTORY));
+}
+END_SETUP()
+
+FN_TEST(invalid_open_how)
+{
+	struct open_how how = { .flags = O_RDONLY };
+	char buf[sizeof(how) + 8] = {};
+
+	TEST_ERRNO(syscall(SYS_openat2, base_fd, "file", &how, sizeof(how) - 1),
+		   EINVAL);
+
+	memcpy(buf, &how, sizeof(how));
+	TEST_RES(syscall(SYS_openat2, base_fd, "file", buf, sizeof(buf)),
+		 is_file(_ret));
+	buf[sizeof(how)] = 1;
+	TEST_ERRNO(syscall(SYS_openat2, base_fd, "file", buf, sizeof(buf)),
+		   E2BIG);
+
+	TEST_ERRNO(sys_openat2(base_fd, "file", O_RDONLY, 0, 0x1000), EINVAL);
+	TEST_ERRNO(sys_openat2(base_fd, "file", O_RDONLY, 0644, 0), EINVAL);
+	TEST_ERRNO(sys_openat2(base_fd, "file", 1ULL << 40, 0, 0), EINVAL);
+	TEST_ERRNO(sys_openat2(base_fd, "file", O_PATH | O_RDWR, 0, 0), EINVAL);
+	TEST_ERRNO(sys_openat2(base_fd, "file", O_RDONLY, 0,
+			       RESOLVE_BENEATH | RESOLVE_IN_ROOT),
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(resolve_beneath)
+{
+	TEST_RES(sys_openat2(base_fd, "file", O_RDONLY, 0, RESOLVE_BENEATH),
+		 is_file(_ret));
+	TEST_RES(sys_openat2(base_fd, "sub/../sub/link_file", O_RDONLY, 0,
+			     RESOLVE_BENEATH),
+		 is_file(_ret));
+
+	TEST_ERRNO(sys_openat2(base_fd, "..", O_RDONLY, 0, RESOLVE_BENEATH),
+		   EXDEV);
+	TEST_ERRNO(sys_openat2(base_fd, "sub/../../openat2_test/file",
+			       O_RDONLY, 0, RESOLVE_BENEATH),
+		   EXDEV);
+	TEST_ERRNO(sys_openat2(base_fd, "link_up", O_RDONLY, 0,
+			       RESOLVE_BENEATH),
+		   EXDEV);
+	TEST_ERRNO(sys_openat2(base_fd, "link_abs", O_RDONLY, 0,
+			       RESOLVE_BENEATH),
+		   EXDEV);
+	TEST_ERRNO(sys_openat2(base_fd, BASE_DIR "/file", O_RDONLY, 0,
+			       RESOLVE_BENEATH),
+		   EXDEV);
+}
+END_TEST()
+
+FN_TEST(resolve_in_root)
+{
+	TEST_RES(sys_openat2(base_fd, "/file", O_RDONLY, 0, RESOLVE_IN_ROOT),
+		 is_file(_ret));
+	TEST_RES(sys_openat2(base_fd, "../../file", O_RDONLY, 0,
+			     RESOLVE_IN_ROOT),
+		 is_file(_ret));
+	TEST_RES(sys_openat2(base_fd, "link_up/file", O_RDONLY, 0,
+			     RESOLVE_IN_ROOT),
+		 is_file(_ret));
+
+	// The absolute symlinks are resolved in the new root.
+	TEST_ERRNO(sys_openat2(base_fd, "link_abs", O_RDONLY, 0,
+			       RESOLVE_IN_ROOT),
+		   ENOENT);
+}
+END_TEST()
+
+FN_TEST(resolve_no_symlinks)
+{
+	TEST_ERRNO(sys_openat2(base_fd, "sub/link_file", O_RDONLY, 0,
+			       RESOLVE_NO_SYMLINKS),
+		   ELOOP);
+	TEST_ERRNO(sys_openat2(base_fd, "link_up/tmp", O_RDONLY, 0,
+			       RESOLVE_NO_SYMLINKS),
+		   ELOOP);
+
+	// The trailing symlink itself can still be opened with `O_PATH`.
+	TEST_SUCC(close(TEST_SUCC(sys_openat2(base_fd, "link_abs",
+					      O_PATH | O_NOFOLLOW, 0,
+					      RESOLVE_NO_SYMLINKS))));
+}
+END_TEST()
+
+FN_TEST(resolve_no_xdev)
+{
+	TEST_RES(sys_openat2(base_fd, "file", O_RDONLY, 0, RESOLVE_NO_XDEV),
+		 is_file(_ret));
+	TEST_ERRNO(sys_openat2(AT_FDCWD, "/proc/self/status", O_RDONLY, 0,
+			       RESOLVE_NO_XDEV),
+		   EXDEV);
+}
+END_TEST()
+
+FN_TEST(resolve_no_magiclinks)
+{
+	char path[64];
+
+	snprintf(path, sizeof(path), "/proc/self/fd/%d", base_fd);
+	TEST_SUCC(close(TEST_SUCC(sys_openat2(AT_FDCWD, path, O_RDONLY, 0, 0))));
+	TEST_ERRNO(sys_openat2(AT_FDCWD, path, O_RDONLY, 0,
+			       RESOLVE_NO_MAGICLINKS),
+		   ELOOP);
+}
+END_TEST()
+
+FN_TEST(resolve_cached)
+{
+	TEST_ERRNO(sys_openat2(base_fd, "file", O_RDWR | O_TRUNC, 0,
+			       RESOLVE_CACHED),
+		   EAGAIN);
+	TEST_ERRNO(sys_openat2(base_fd, "new", O_RDWR | O_CREAT, 0644,
+			       RESOLVE_CACHED),
+		   EAGAIN);
+
+	// The file has been looked up, so it must be cached.
+	TEST_RES(sys_openat2(base_fd, "file", O_RDONLY, 0, RESOLVE_CACHED),
+		 is_file(_ret));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(base_fd));
+	CHECK(unlink(BASE_DIR "/sub/link_file"));
+	CHECK(unlink(BASE_DIR "/link_up"));
+	CHECK(unlink(BASE_DIR "/link_abs"));
+	CHECK(unlink(BASE_DIR "/file"));
+	CHECK(rmdir(BASE_DIR "/sub"));
+	CHECK(rmdir(BASE_DIR));
+}
+END_SETUP()
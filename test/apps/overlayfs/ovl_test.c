@@ -115,6 +115,7 @@ void clean_up()
 	umount(MERGEDDIR);
 	umount(OVERLAYDIR);
 	rmdir(MERGEDDIR);
+	rmdir(WORKDIR "/work");
 	rmdir(WORKDIR);
 	rmdir(UPPERDIR);
 	rmdir(LOWERDIR1);
@@ -181,6 +182,34 @@ int main()
 		buffer, "new content for f1",
 		"Content of /overlay/merged/f1 should be 'new content for f1'");
 
+	// Rename test - The lower file is hidden by a whiteout
+	printf("Renaming /overlay/merged/f2 to /overlay/merged/f3\n");
+	if (rename(MERGEDDIR "/f2", MERGEDDIR "/f3") == -1) {
+		handle_error("rename");
+	}
+
+	read_file(MERGEDDIR "/f3", buffer, sizeof(buffer));
+	assert_eq(buffer, "8899",
+		  "Content of /overlay/merged/f3 should be '8899' after renaming");
+	if (access(MERGEDDIR "/f2", F_OK) != -1 || errno != ENOENT) {
+		handle_error("access /overlay/merged/f2");
+	}
+
+	// The whiteout is a character device with the device number 0/0
+	struct stat st;
+	if (stat(UPPERDIR "/f2", &st) == -1) {
+		handle_error("stat");
+	}
+	if (!S_ISCHR(st.st_mode) || st.st_rdev != 0) {
+		fprintf(stderr, "/overlay/upper/f2 should be a whiteout\n");
+		handle_error("whiteout");
+	}
+
+	// The merged directories cannot be renamed
+	if (rename(MERGEDDIR "/d1", MERGEDDIR "/d2") != -1 || errno != EXDEV) {
+		handle_error("rename /overlay/merged/d1");
+	}
+
 	// Clean up before exit
 	printf("Cleaning up\n");
 	clean_up();
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/sendfile.h>
+#include <sys/uio.h>
+
+#include "../network/test.h"
+
+#define FILE_NAME "/tmp/splice_test_file"
+
+static int rfd1, wfd1;
+static int rfd2, wfd2;
+static int file_fd;
+
+FN_SETUP(pipes_and_file)
+{
+	int fildes[2];
+
+	CHECK(pipe(fildes));
+	rfd1 = fildes[0];
+	wfd1 = fildes[1];
+
+	CHECK(pipe(fildes));
+	rfd2 = fildes[0];
+	wfd2 = fildes[1];
+
+	file_fd = CHECK(open(FILE_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	CHECK_WITH(write(file_fd, "0123456789", 10), _ret == 10);
+}
+END_SETUP()
+
+FN_TEST(splice_file_to_pipe)
+{
+	loff_t off = 2;
+	char buf[16];
+
+	TEST_RES(splice(file_fd, &off, wfd1, NULL, 4, 0),
+		 _ret == 4 && off == 6);
+	TEST_RES(read(rfd1, buf, sizeof(buf)),
+		 _ret == 4 && memcmp(buf, "2345", 4) == 0);
+
+	// The file offset is used and advanced if no offset is given.
+	TEST_RES(lseek(file_fd, 8, SEEK_SET), _ret == 8);
+	TEST_RES(splice(file_fd, NULL, wfd1, NULL, 16, 0), _ret == 2);
+	TEST_RES(lseek(file_fd, 0, SEEK_CUR), _ret == 10);
+	TEST_RES(read(rfd1, buf, sizeof(buf)),
+		 _ret == 2 && memcmp(buf, "89", 2) == 0);
+}
+END_TEST()
+
+FN_TEST(splice_pipe_to_file)
+{
+	loff_t off = 10;
+	char buf[16];
+
+	TEST_RES(write(wfd1, "abc", 3), _ret == 3);
+	TEST_RES(splice(rfd1, NULL, file_fd, &off, 16, SPLICE_F_NONBLOCK),
+		 _ret == 3 && off == 13);
+	TEST_RES(pread(file_fd, buf, sizeof(buf), 10),
+		 _ret == 3 && memcmp(buf, "abc", 3) == 0);
+
+	TEST_ERRNO(splice(rfd1, NULL, file_fd, &off, 16, SPLICE_F_NONBLOCK),
+		   EAGAIN);
+}
+END_TEST()
+
+FN_TEST(splice_errors)
+{
+	loff_t off = 0;
+
+	TEST_ERRNO(splice(file_fd, NULL, file_fd, NULL, 1, 0), EINVAL);
+	TEST_ERRNO(splice(rfd1, &off, wfd2, NULL, 1, 0), ESPIPE);
+	TEST_ERRNO(splice(file_fd, NULL, rfd1, NULL, 1, 0), EBADF);
+	TEST_ERRNO(splice(file_fd, NULL, wfd1, NULL, 1, 0x100), EINVAL);
+}
+END_TEST()
+
+FN_TEST(tee_and_splice_pipe_to_pipe)
+{
+	char buf[16];
+
+	TEST_RES(write(wfd1, "hello", 5), _ret == 5);
+
+	// `tee` duplicates the data without consuming it.
+	TEST_RES(tee(rfd1, wfd2, 16, 0), _ret == 5);
+	TEST_RES(read(rfd2, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+
+	// `splice` moves the data.
+	TEST_RES(splice(rfd1, NULL, wfd2, NULL, 16, 0), _ret == 5);
+	TEST_ERRNO(splice(rfd1, NULL, wfd2, NULL, 16, SPLICE_F_NONBLOCK),
+		   EAGAIN);
+	TEST_RES(read(rfd2, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+
+	TEST_ERRNO(tee(rfd1, wfd2, 16, SPLICE_F_NONBLOCK), EAGAIN);
+	TEST_ERRNO(tee(file_fd, wfd2, 16, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(vmsplice)
+{
+	char buf[16];
+	struct iovec iov[2] = {
+		{ .iov_base = "ab", .iov_len = 2 },
+		{ .iov_base = "cde", .iov_len = 3 },
+	};
+
+	TEST_RES(vmsplice(wfd1, iov, 2, 0), _ret == 5);
+	TEST_RES(read(rfd1, buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "abcde", 5) == 0);
+
+	TEST_ERRNO(vmsplice(file_fd, iov, 2, 0), EBADF);
+}
+END_TEST()
+
+FN_TEST(sendfile_to_pipe)
+{
+	off_t off = 0;
+	char buf[16];
+
+	TEST_RES(sendfile(wfd1, file_fd, &off, 4), _ret == 4 && off == 4);
+	TEST_RES(read(rfd1, buf, sizeof(buf)),
+		 _ret == 4 && memcmp(buf, "0123", 4) == 0);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(rfd1));
+	CHECK(close(wfd1));
+	CHECK(close(rfd2));
+	CHECK(close(wfd2));
+	CHECK(close(file_fd));
+	CHECK(unlink(FILE_NAME));
+}
+END_SETUP()
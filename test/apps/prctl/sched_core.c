@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <signal.h>
+#include <stdint.h>
+#include <sys/prctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#ifndef PR_SCHED_CORE
+#define PR_SCHED_CORE 62
+#define PR_SCHED_CORE_GET 0
+#define PR_SCHED_CORE_CREATE 1
+#define PR_SCHED_CORE_SHARE_TO 2
+#define PR_SCHED_CORE_SHARE_FROM 3
+#define PR_SCHED_CORE_SCOPE_THREAD 0
+#define PR_SCHED_CORE_SCOPE_THREAD_GROUP 1
+#define PR_SCHED_CORE_SCOPE_PROCESS_GROUP 2
+#endif
+
+static uint64_t cookie;
+
+static int get_cookie(pid_t pid)
+{
+	return prctl(PR_SCHED_CORE, PR_SCHED_CORE_GET, pid,
+		     PR_SCHED_CORE_SCOPE_THREAD, &cookie);
+}
+
+static int sched_core(int cmd, pid_t pid, int scope)
+{
+	return prctl(PR_SCHED_CORE, cmd, pid, scope, 0);
+}
+
+// Forks a child that waits until it is killed.
+static pid_t fork_sleeper(void)
+{
+	pid_t pid;
+
+	pid = fork();
+	if (pid == 0) {
+		for (;;)
+			pause();
+	}
+	return pid;
+}
+
+static int kill_and_wait(pid_t pid)
+{
+	if (kill(pid, SIGKILL) < 0)
+		return -1;
+	return waitpid(pid, NULL, 0) == pid ? 0 : -1;
+}
+
+FN_TEST(invalid_args)
+{
+	TEST_ERRNO(prctl(PR_SCHED_CORE, PR_SCHED_CORE_GET, 0,
+			 PR_SCHED_CORE_SCOPE_THREAD_GROUP, &cookie),
+		   EINVAL);
+	TEST_ERRNO(sched_core(PR_SCHED_CORE_SHARE_FROM, 0,
+			      PR_SCHED_CORE_SCOPE_PROCESS_GROUP),
+		   EINVAL);
+	TEST_ERRNO(sched_core(PR_SCHED_CORE_CREATE, 0, 3), EINVAL);
+	TEST_ERRNO(sched_core(4, 0, PR_SCHED_CORE_SCOPE_THREAD), EINVAL);
+
+	TEST_ERRNO(get_cookie(1234567890), ESRCH);
+	TEST_ERRNO(sched_core(PR_SCHED_CORE_CREATE, 1234567890,
+			      PR_SCHED_CORE_SCOPE_THREAD_GROUP),
+		   ESRCH);
+	TEST_ERRNO(sched_core(PR_SCHED_CORE_SHARE_TO, 1234567890,
+			      PR_SCHED_CORE_SCOPE_PROCESS_GROUP),
+		   ESRCH);
+}
+END_TEST()
+
+FN_TEST(create)
+{
+	uint64_t old_cookie;
+
+	TEST_RES(get_cookie(0), cookie == 0);
+
+	TEST_SUCC(sched_core(PR_SCHED_CORE_CREATE, 0,
+			     PR_SCHED_CORE_SCOPE_THREAD));
+	TEST_RES(get_cookie(0), cookie != 0);
+	old_cookie = cookie;
+
+	TEST_SUCC(sched_core(PR_SCHED_CORE_CREATE, 0,
+			     PR_SCHED_CORE_SCOPE_THREAD_GROUP));
+	TEST_RES(get_cookie(0), cookie != 0 && cookie != old_cookie);
+	TEST_RES(get_cookie(getpid()), cookie != 0 && cookie != old_cookie);
+}
+END_TEST()
+
+FN_TEST(inherit_on_fork)
+{
+	uint64_t parent_cookie;
+	pid_t pid;
+	int status;
+
+	TEST_RES(get_cookie(0), cookie != 0);
+	parent_cookie = cookie;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (get_cookie(0) < 0 || cookie != parent_cookie)
+			_exit(EXIT_FAILURE);
+		_exit(EXIT_SUCCESS);
+	}
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) &&
+			 WEXITSTATUS(status) == EXIT_SUCCESS);
+}
+END_TEST()
+
+FN_TEST(share_to_and_from)
+{
+	uint64_t parent_cookie, child_cookie;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork_sleeper());
+
+	// Gives the child a cookie of its own.
+	TEST_SUCC(sched_core(PR_SCHED_CORE_CREATE, pid,
+			     PR_SCHED_CORE_SCOPE_THREAD));
+	TEST_RES(get_cookie(0), cookie != 0);
+	parent_cookie = cookie;
+	TEST_RES(get_cookie(pid), cookie != 0 && cookie != parent_cookie);
+	child_cookie = cookie;
+
+	// Pulls the cookie of the child.
+	TEST_SUCC(sched_core(PR_SCHED_CORE_SHARE_FROM, pid,
+			     PR_SCHED_CORE_SCOPE_THREAD));
+	TEST_RES(get_cookie(0), cookie == child_cookie);
+
+	// Pushes a new cookie to the child.
+	TEST_SUCC(sched_core(PR_SCHED_CORE_CREATE, 0,
+			     PR_SCHED_CORE_SCOPE_THREAD));
+	TEST_RES(get_cookie(0), cookie != child_cookie);
+	parent_cookie = cookie;
+	TEST_SUCC(sched_core(PR_SCHED_CORE_SHARE_TO, pid,
+			     PR_SCHED_CORE_SCOPE_THREAD_GROUP));
+	TEST_RES(get_cookie(pid), cookie == parent_cookie);
+
+	TEST_SUCC(kill_and_wait(pid));
+}
+END_TEST()
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define HEADER "#\n# Asterinas kernel configuration\n#\n"
+
+static char buf[16384];
+
+// Reads the whole file into `buf` and terminates it with a null byte.
+static int read_config(void)
+{
+	int fd;
+	ssize_t len, total = 0;
+
+	fd = open("/proc/config", O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	while ((len = read(fd, buf + total, sizeof(buf) - 1 - total)) > 0)
+		total += len;
+
+	close(fd);
+	if (len < 0)
+		return -1;
+
+	buf[total] = '\0';
+	return total;
+}
+
+static int has_line(const char *line)
+{
+	const char *found = buf;
+	size_t len = strlen(line);
+
+	while ((found = strstr(found, line)) != NULL) {
+		if ((found == buf || found[-1] == '\n') && found[len] == '\n')
+			return 1;
+		found += len;
+	}
+	return 0;
+}
+
+// Checks whether the option is listed, either enabled or disabled.
+static int has_option(const char *name)
+{
+	char line[128];
+
+	snprintf(line, sizeof(line), "CONFIG_%s=y", name);
+	if (has_line(line))
+		return 1;
+	snprintf(line, sizeof(line), "# CONFIG_%s is not set", name);
+	return has_line(line);
+}
+
+// Checks that every line is either a comment or an enabled option, like the
+// `.config` file of Linux.
+static int is_well_formed(void)
+{
+	const char *line = buf;
+	const char *end;
+
+	while (*line != '\0') {
+		end = strchr(line, '\n');
+		if (end == NULL)
+			return 0;
+		if (*line != '#' &&
+		    (strncmp(line, "CONFIG_", 7) != 0 ||
+		     end - line < 3 || strncmp(end - 2, "=y", 2) != 0))
+			return 0;
+		line = end + 1;
+	}
+	return 1;
+}
+
+FN_TEST(config)
+{
+	TEST_RES(read_config(),
+		 _ret > 0 && strncmp(buf, HEADER, strlen(HEADER)) == 0);
+	TEST_RES(is_well_formed(), _ret);
+
+	// The options declared in `OSDK.toml` are always listed when the kernel
+	// is built by the OSDK.
+	TEST_RES(has_option("CVM_GUEST"), _ret);
+	TEST_RES(has_option("NO_SUCH_OPTION"), !_ret);
+}
+END_TEST()
@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <signal.h>
+#include <stdint.h>
+#include <sys/prctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define USER_A 1000
+#define USER_B 2000
+
+static char secret[] = "the secret in /proc/[pid]/mem";
+
+static pid_t target_same_user;
+static pid_t target_other_user;
+static pid_t target_non_dumpable;
+
+// Spawns a process that runs as `uid` and waits to be killed.
+static pid_t spawn_target(uid_t uid, int is_dumpable)
+{
+	int fds[2];
+	pid_t pid;
+	char c;
+
+	CHECK(pipe(fds));
+
+	pid = CHECK(fork());
+	if (pid == 0) {
+		close(fds[0]);
+		if (setresgid(uid, uid, uid) < 0 ||
+		    setresuid(uid, uid, uid) < 0 ||
+		    prctl(PR_SET_DUMPABLE, is_dumpable) < 0)
+			_exit(EXIT_FAILURE);
+		if (write(fds[1], "", 1) != 1)
+			_exit(EXIT_FAILURE);
+		for (;;)
+			pause();
+	}
+
+	close(fds[1]);
+	CHECK_WITH(read(fds[0], &c, 1), _ret == 1);
+	close(fds[0]);
+
+	return pid;
+}
+
+// Reads the secret from the memory of `pid` via `fd`, or via a newly opened
+// file if `fd` is negative. Returns zero on success, or the error number.
+static int read_secret(pid_t pid, int fd)
+{
+	char path[32];
+	char buf[sizeof(secret)];
+	ssize_t len;
+	int is_opened = 0;
+
+	if (fd < 0) {
+		snprintf(path, sizeof(path), "/proc/%d/mem", pid);
+		fd = open(path, O_RDONLY);
+		if (fd < 0)
+			return errno;
+		is_opened = 1;
+	}
+
+	len = pread(fd, buf, sizeof(buf), (off_t)(uintptr_t)secret);
+	if (len < 0)
+		len = -errno;
+	if (is_opened)
+		close(fd);
+
+	if (len < 0)
+		return -len;
+	if (len != sizeof(buf) || memcmp(buf, secret, sizeof(buf)) != 0)
+		return EIO;
+	return 0;
+}
+
+// Reads the secret like `read_secret`, but in a child process that runs as
+// `uid` without any capabilities.
+static int read_secret_as(uid_t uid, pid_t pid, int fd)
+{
+	pid_t child;
+	int status;
+
+	child = CHECK(fork());
+	if (child == 0) {
+		if (setresgid(uid, uid, uid) < 0 ||
+		    setresuid(uid, uid, uid) < 0 ||
+		    prctl(PR_SET_DUMPABLE, 1) < 0)
+			_exit(UINT8_MAX);
+		_exit(read_secret(pid, fd));
+	}
+
+	CHECK_WITH(waitpid(child, &status, 0),
+		   _ret == child && WIFEXITED(status));
+	return WEXITSTATUS(status);
+}
+
+FN_SETUP(spawn_targets)
+{
+	target_same_user = spawn_target(USER_A, 1);
+	target_other_user = spawn_target(USER_B, 1);
+	target_non_dumpable = spawn_target(USER_A, 0);
+}
+END_SETUP()
+
+FN_TEST(same_user)
+{
+	TEST_RES(read_secret_as(USER_A, target_same_user, -1), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(other_user)
+{
+	TEST_RES(read_secret_as(USER_A, target_other_user, -1),
+		 _ret == EACCES);
+
+	// `CAP_SYS_PTRACE` allows reading the memory of other users.
+	TEST_RES(read_secret(target_other_user, -1), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(non_dumpable)
+{
+	TEST_RES(read_secret_as(USER_A, target_non_dumpable, -1),
+		 _ret == EACCES);
+
+	// `CAP_SYS_PTRACE` allows reading the memory of non-dumpable processes.
+	TEST_RES(read_secret(target_non_dumpable, -1), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(open_time_credentials)
+{
+	char path[32];
+	int fd;
+
+	// The access is checked with the credentials of the opener, so the
+	// file opened with `CAP_SYS_PTRACE` can still be read after the
+	// capability is dropped.
+	snprintf(path, sizeof(path), "/proc/%d/mem", target_non_dumpable);
+	fd = TEST_SUCC(open(path, O_RDONLY));
+	TEST_RES(read_secret_as(USER_A, target_non_dumpable, fd), _ret == 0);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+// Opens the memory of the current process as `USER_A`, and reads it in a child
+// that inherits the file after the current process becomes non-dumpable.
+//
+// Unlike Linux, which only binds the file to the address space, the access is
+// checked again whenever the memory is read by another process.
+static int read_inherited_self_mem(void)
+{
+	int fd;
+
+	if (setresgid(USER_A, USER_A, USER_A) < 0 ||
+	    setresuid(USER_A, USER_A, USER_A) < 0 ||
+	    prctl(PR_SET_DUMPABLE, 1) < 0)
+		return UINT8_MAX;
+
+	fd = open("/proc/self/mem", O_RDONLY);
+	if (fd < 0 || read_secret(getpid(), fd) != 0)
+		return UINT8_MAX;
+
+	// The process itself can always read its memory.
+	if (prctl(PR_SET_DUMPABLE, 0) < 0 || read_secret(getpid(), fd) != 0)
+		return UINT8_MAX;
+
+	return read_secret_as(USER_A, getpid(), fd);
+}
+
+FN_TEST(inherited_self_mem)
+{
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(read_inherited_self_mem());
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) &&
+			 WEXITSTATUS(status) == EACCES);
+}
+END_TEST()
+
+FN_SETUP(kill_targets)
+{
+	CHECK(kill(target_same_user, SIGKILL));
+	CHECK(kill(target_other_user, SIGKILL));
+	CHECK(kill(target_non_dumpable, SIGKILL));
+	CHECK(waitpid(target_same_user, NULL, 0));
+	CHECK(waitpid(target_other_user, NULL, 0));
+	CHECK(waitpid(target_non_dumpable, NULL, 0));
+}
+END_SETUP()
@@ -0,0 +1,381 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <signal.h>
+#include <stdint.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define FILE_NAME "/tmp/proc_pid_test_file"
+#define PAGE_SIZE 4096
+
+extern char **environ;
+
+static char buf[16384];
+static char path[64];
+
+// Reads the whole file into `buf` and terminates it with a null byte.
+static int read_file(const char *name)
+{
+	int fd;
+	ssize_t len, total = 0;
+
+	fd = open(name, O_RDONLY);
+	if (fd < 0)
+		return -1;
+
+	while ((len = read(fd, buf + total, sizeof(buf) - 1 - total)) > 0)
+		total += len;
+
+	close(fd);
+	if (len < 0)
+		return -1;
+
+	buf[total] = '\0';
+	return total;
+}
+
+// Finds the line that starts with `prefix`, and returns the rest of the line.
+static const char *find_field(const char *prefix)
+{
+	const char *line = buf;
+	size_t len = strlen(prefix);
+
+	while (line != NULL && *line != '\0') {
+		if (strncmp(line, prefix, len) == 0)
+			return line + len;
+		line = strchr(line, '\n');
+		if (line != NULL)
+			line++;
+	}
+
+	return NULL;
+}
+
+static int field_is(const char *prefix, const char *value)
+{
+	const char *field = find_field(prefix);
+
+	return field != NULL && strncmp(field, value, strlen(value)) == 0 &&
+	       field[strlen(value)] == '\n';
+}
+
+// Reads the flags in `/proc/self/fdinfo/<fd>`.
+static int read_fdinfo_flags(const char *name)
+{
+	const char *field;
+
+	if (read_file(name) < 0)
+		return -1;
+
+	field = find_field("flags:\t");
+	if (field == NULL) {
+		errno = EINVAL;
+		return -1;
+	}
+	return strtol(field, NULL, 8);
+}
+
+static int readlink_is(const char *name, const char *target)
+{
+	ssize_t len;
+
+	len = readlink(name, buf, sizeof(buf) - 1);
+	if (len < 0)
+		return -1;
+
+	buf[len] = '\0';
+	return strcmp(buf, target) == 0;
+}
+
+FN_TEST(cwd_and_root)
+{
+	snprintf(path, sizeof(path), "/proc/%d/cwd", getpid());
+
+	TEST_SUCC(chdir("/tmp"));
+	TEST_RES(readlink_is("/proc/self/cwd", "/tmp"), _ret == 1);
+	TEST_RES(readlink_is(path, "/tmp"), _ret == 1);
+
+	TEST_SUCC(chdir("/"));
+	TEST_RES(readlink_is("/proc/self/cwd", "/"), _ret == 1);
+	TEST_RES(readlink_is(path, "/"), _ret == 1);
+
+	TEST_RES(readlink_is("/proc/self/root", "/"), _ret == 1);
+}
+END_TEST()
+
+static int is_environ_correct(int len)
+{
+	char **env;
+	int offset = 0;
+
+	for (env = environ; *env != NULL; env++) {
+		if (strcmp(buf + offset, *env) != 0)
+			return 0;
+		offset += strlen(*env) + 1;
+	}
+
+	return offset == len;
+}
+
+FN_TEST(environ)
+{
+	TEST_RES(read_file("/proc/self/environ"), is_environ_correct(_ret));
+}
+END_TEST()
+
+FN_TEST(fdinfo)
+{
+	struct stat st;
+	char expected[32];
+	int fd;
+
+	fd = TEST_SUCC(open(FILE_NAME, O_CREAT | O_TRUNC | O_RDWR | O_CLOEXEC,
+			    0644));
+	TEST_RES(write(fd, "hello", 5), _ret == 5);
+	TEST_RES(lseek(fd, 3, SEEK_SET), _ret == 3);
+	TEST_SUCC(fstat(fd, &st));
+
+	snprintf(path, sizeof(path), "/proc/self/fdinfo/%d", fd);
+	snprintf(expected, sizeof(expected), "%lu", (unsigned long)st.st_ino);
+	TEST_RES(read_file(path),
+		 field_is("pos:\t", "3") && field_is("ino:\t", expected));
+	TEST_RES(read_fdinfo_flags(path),
+		 (_ret & O_ACCMODE) == O_RDWR && (_ret & O_CLOEXEC) != 0 &&
+			 (_ret & O_APPEND) == 0);
+
+	TEST_SUCC(fcntl(fd, F_SETFD, 0));
+	TEST_SUCC(fcntl(fd, F_SETFL, O_APPEND));
+	TEST_RES(read_fdinfo_flags(path),
+		 (_ret & O_CLOEXEC) == 0 && (_ret & O_APPEND) != 0);
+
+	TEST_SUCC(close(fd));
+	TEST_ERRNO(open(path, O_RDONLY), ENOENT);
+	TEST_SUCC(unlink(FILE_NAME));
+}
+END_TEST()
+
+// Checks whether the mapping at `addr` is listed with `perms` and `name`.
+static int has_mapping(void *addr, const char *perms, const char *name)
+{
+	char start[32];
+	const char *line;
+
+	snprintf(start, sizeof(start), "%08lx-", (unsigned long)addr);
+	line = strstr(buf, start);
+	if (line == NULL || (line != buf && line[-1] != '\n'))
+		return 0;
+
+	line = strchr(line, ' ');
+	if (line == NULL || strncmp(line + 1, perms, 4) != 0)
+		return 0;
+
+	line = strchr(line, '\n');
+	if (line == NULL)
+		return 0;
+	if (name == NULL)
+		return line[-1] == ' ' || line[-1] == '0';
+	return strlen(name) <= (size_t)(line - buf) &&
+	       strncmp(line - strlen(name), name, strlen(name)) == 0;
+}
+
+FN_TEST(maps)
+{
+	void *anon_addr, *file_addr;
+	int fd;
+
+	anon_addr = (void *)TEST_SUCC((long)mmap(NULL, PAGE_SIZE, PROT_READ,
+						 MAP_PRIVATE | MAP_ANONYMOUS,
+						 -1, 0));
+
+	fd = TEST_SUCC(open(FILE_NAME, O_CREAT | O_TRUNC | O_RDWR, 0644));
+	TEST_SUCC(ftruncate(fd, PAGE_SIZE));
+	file_addr = (void *)TEST_SUCC((long)mmap(NULL, PAGE_SIZE,
+						 PROT_READ | PROT_WRITE,
+						 MAP_SHARED, fd, 0));
+
+	TEST_RES(read_file("/proc/self/maps"),
+		 has_mapping(anon_addr, "r--p", NULL) &&
+			 has_mapping(file_addr, "rw-s", FILE_NAME));
+	TEST_RES(strstr(buf, " [stack]\n") != NULL, _ret);
+	TEST_RES(strstr(buf, " [vdso]\n") != NULL, _ret);
+
+	TEST_SUCC(munmap(file_addr, PAGE_SIZE));
+	TEST_SUCC(munmap(anon_addr, PAGE_SIZE));
+	TEST_RES(read_file("/proc/self/maps"),
+		 !has_mapping(anon_addr, "r--p", NULL) &&
+			 !has_mapping(file_addr, "rw-s", FILE_NAME));
+
+	TEST_SUCC(close(fd));
+	TEST_SUCC(unlink(FILE_NAME));
+}
+END_TEST()
+
+FN_TEST(status)
+{
+	struct sigaction sa = { .sa_handler = SIG_IGN };
+	char expected[32];
+	sigset_t set;
+
+	sigemptyset(&set);
+	sigaddset(&set, SIGUSR1);
+	TEST_SUCC(sigprocmask(SIG_SETMASK, &set, NULL));
+	TEST_SUCC(sigaction(SIGUSR2, &sa, NULL));
+
+	TEST_RES(read_file("/proc/self/status"),
+		 field_is("State:\t", "R (running)"));
+
+	snprintf(expected, sizeof(expected), "%d", getpid());
+	TEST_RES(field_is("Pid:\t", expected), _ret);
+	TEST_RES(field_is("Tgid:\t", expected), _ret);
+	snprintf(expected, sizeof(expected), "%d", getppid());
+	TEST_RES(field_is("PPid:\t", expected), _ret);
+
+	TEST_RES(field_is("Uid:\t", "0\t0\t0\t0"), _ret);
+	TEST_RES(field_is("Gid:\t", "0\t0\t0\t0"), _ret);
+	TEST_RES(field_is("Threads:\t", "1"), _ret);
+	TEST_RES(field_is("SigBlk:\t", "0000000000000200"), _ret);
+	TEST_RES(field_is("SigIgn:\t", "0000000000000800"), _ret);
+	TEST_RES(find_field("VmSize:\t") != NULL, _ret);
+	TEST_RES(find_field("CapEff:\t") != NULL, _ret);
+
+	sigemptyset(&set);
+	TEST_SUCC(sigprocmask(SIG_SETMASK, &set, NULL));
+	sa.sa_handler = SIG_DFL;
+	TEST_SUCC(sigaction(SIGUSR2, &sa, NULL));
+}
+END_TEST()
+
+static char child_buf[16];
+
+// Waits until the child is blocked, and leaves `/proc/[pid]/syscall` in `buf`.
+static int wait_until_blocked(pid_t pid)
+{
+	int i;
+
+	snprintf(path, sizeof(path), "/proc/%d/syscall", pid);
+	for (i = 0; i < 1000; i++) {
+		if (read_file(path) < 0)
+			return -1;
+		if (strcmp(buf, "running\n") != 0)
+			return 0;
+		usleep(1000);
+	}
+
+	errno = ETIMEDOUT;
+	return -1;
+}
+
+// Checks that `buf` shows the child blocked in reading `fd`, i.e.,
+// `<num> <args>... <sp> <pc>` with six arguments.
+static int is_blocked_in_read(int fd)
+{
+	unsigned long args[6], sp, pc;
+	long num;
+
+	if (sscanf(buf, "%ld %lx %lx %lx %lx %lx %lx %lx %lx", &num, &args[0],
+		   &args[1], &args[2], &args[3], &args[4], &args[5], &sp,
+		   &pc) != 9)
+		return 0;
+
+	return num == SYS_read && args[0] == (unsigned long)fd &&
+	       args[1] == (unsigned long)child_buf &&
+	       args[2] == sizeof(child_buf) && sp != 0 && pc != 0 &&
+	       buf[strlen(buf) - 1] == '\n';
+}
+
+// Checks that `buf` is a source location, i.e., `<file>:<line>` followed by
+// `suffix`. Linux shows the name of a kernel function instead.
+static int is_location(const char *start, const char *suffix)
+{
+	const char *colon = strrchr(start, ':');
+	char *end;
+
+	if (colon == NULL || colon == start)
+		return 0;
+	if (strtol(colon + 1, &end, 10) <= 0 || end == colon + 1)
+		return 0;
+	return strcmp(end, suffix) == 0;
+}
+
+// Reads `/proc/[pid]/stack` as an unprivileged user in a child.
+static int read_stack_unprivileged(pid_t pid)
+{
+	pid_t child;
+	int status;
+
+	child = fork();
+	if (child == 0) {
+		if (setresuid(1000, 1000, 1000) < 0)
+			_exit(EXIT_FAILURE);
+		snprintf(path, sizeof(path), "/proc/%d/stack", pid);
+		_exit(read_file(path) < 0 ? errno : 0);
+	}
+
+	if (child < 0 || waitpid(child, &status, 0) != child ||
+	    !WIFEXITED(status))
+		return -1;
+	return WEXITSTATUS(status);
+}
+
+FN_TEST(blocked_child)
+{
+	int fds[2];
+	pid_t pid;
+
+	TEST_SUCC(pipe(fds));
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		close(fds[1]);
+		_exit(read(fds[0], child_buf, sizeof(child_buf)) < 0);
+	}
+
+	TEST_SUCC(wait_until_blocked(pid));
+	TEST_RES(is_blocked_in_read(fds[0]), _ret);
+
+	// Like Linux, there is no trailing newline.
+	snprintf(path, sizeof(path), "/proc/%d/wchan", pid);
+	TEST_RES(read_file(path), _ret > 0 && is_location(buf, ""));
+
+	// Only the frame that makes the blocking call is shown.
+	snprintf(path, sizeof(path), "/proc/%d/stack", pid);
+	TEST_RES(read_file(path), _ret > 0 && strncmp(buf, "[<0>] ", 6) == 0 &&
+					  is_location(buf + 6, "\n"));
+	TEST_RES(read_stack_unprivileged(pid), _ret == EACCES);
+
+	TEST_RES(write(fds[1], "", 1), _ret == 1);
+	TEST_RES(waitpid(pid, NULL, 0), _ret == pid);
+	TEST_SUCC(close(fds[0]));
+	TEST_SUCC(close(fds[1]));
+}
+END_TEST()
+
+FN_TEST(running_child)
+{
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		for (;;)
+			;
+	}
+
+	snprintf(path, sizeof(path), "/proc/%d/syscall", pid);
+	TEST_RES(read_file(path), strcmp(buf, "running\n") == 0);
+	snprintf(path, sizeof(path), "/proc/%d/wchan", pid);
+	TEST_RES(read_file(path), strcmp(buf, "0") == 0);
+	// Unlike Linux, no frame is shown if the child is not blocked.
+	snprintf(path, sizeof(path), "/proc/%d/stack", pid);
+	TEST_RES(read_file(path), _ret == 0);
+
+	TEST_SUCC(kill(pid, SIGKILL));
+	TEST_RES(waitpid(pid, NULL, 0), _ret == pid);
+}
+END_TEST()
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define GLOBAL_DENY "/proc/sys/kernel/syscall_deny"
+#define GLOBAL_LOG "/proc/sys/kernel/syscall_log"
+
+static char buf[256];
+
+static int write_list(const char *path, const char *command)
+{
+	int fd, ret;
+
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	ret = write(fd, command, strlen(command));
+	close(fd);
+	return ret;
+}
+
+static int read_list(const char *path)
+{
+	int fd, len;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (len < 0)
+		return -1;
+	buf[len] = '\0';
+	return len;
+}
+
+FN_TEST(update_list)
+{
+	char command[64];
+
+	TEST_RES(read_list(GLOBAL_LOG), strcmp(buf, "\n") == 0);
+
+	snprintf(command, sizeof(command), "%d %d", SYS_getppid, SYS_getpid);
+	TEST_RES(write_list(GLOBAL_LOG, command), _ret == strlen(command));
+	TEST_RES(read_list(GLOBAL_LOG), _ret > 0 && strchr(buf, ' ') != NULL);
+
+	snprintf(command, sizeof(command), "-%d", SYS_getpid);
+	TEST_RES(write_list(GLOBAL_LOG, command), _ret == strlen(command));
+	snprintf(command, sizeof(command), "%d\n", SYS_getppid);
+	TEST_RES(read_list(GLOBAL_LOG), strcmp(buf, command) == 0);
+
+	TEST_ERRNO(write_list(GLOBAL_LOG, "+100000"), EINVAL);
+	TEST_ERRNO(write_list(GLOBAL_LOG, "+abc"), EINVAL);
+	TEST_RES(read_list(GLOBAL_LOG), strcmp(buf, command) == 0);
+
+	TEST_RES(write_list(GLOBAL_LOG, "\n"), _ret == 1);
+	TEST_RES(read_list(GLOBAL_LOG), strcmp(buf, "\n") == 0);
+}
+END_TEST()
+
+FN_TEST(global_deny)
+{
+	char command[64];
+
+	snprintf(command, sizeof(command), "+%d", SYS_getppid);
+	TEST_RES(write_list(GLOBAL_DENY, command), _ret == strlen(command));
+	TEST_ERRNO(syscall(SYS_getppid), ENOSYS);
+	TEST_SUCC(syscall(SYS_getpid));
+
+	TEST_RES(write_list(GLOBAL_DENY, ""), _ret == 0);
+	TEST_SUCC(syscall(SYS_getppid));
+}
+END_TEST()
+
+FN_TEST(process_deny)
+{
+	char path[64], command[64];
+	int pipefd[2], status;
+	pid_t pid;
+	char c;
+
+	TEST_SUCC(pipe(pipefd));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		close(pipefd[1]);
+		if (read(pipefd[0], &c, 1) != 1)
+			_exit(1);
+		if (syscall(SYS_getppid) >= 0 || errno != ENOSYS)
+			_exit(2);
+
+		// The deny list is inherited by the children.
+		pid = fork();
+		if (pid == 0)
+			_exit(syscall(SYS_getppid) >= 0 || errno != ENOSYS);
+		if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) ||
+		    WEXITSTATUS(status) != 0)
+			_exit(3);
+		_exit(0);
+	}
+
+	snprintf(path, sizeof(path), "/proc/%d/syscall_deny", pid);
+	snprintf(command, sizeof(command), "%d", SYS_getppid);
+	TEST_RES(write_list(path, command), _ret == strlen(command));
+	snprintf(command, sizeof(command), "%d\n", SYS_getppid);
+	TEST_RES(read_list(path), strcmp(buf, command) == 0);
+
+	// The deny list of other processes is not affected.
+	TEST_RES(read_list("/proc/self/syscall_deny"), strcmp(buf, "\n") == 0);
+	TEST_SUCC(syscall(SYS_getppid));
+
+	TEST_RES(write(pipefd[1], "a", 1), _ret == 1);
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	TEST_SUCC(close(pipefd[0]));
+	TEST_SUCC(close(pipefd[1]));
+}
+END_TEST()
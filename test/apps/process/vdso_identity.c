@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <stdint.h>
+#include <sys/auxv.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+// The identity page lies between the vDSO data and the vDSO text, whose start
+// is reported by `AT_SYSINFO_EHDR`.
+#define VDSO_TEXT_OFFSET (4 * PAGE_SIZE)
+#define VDSO_IDENTITY_OFFSET PAGE_SIZE
+
+struct vdso_identity {
+	uint64_t generation;
+	uint32_t pid;
+	uint32_t uid;
+	uint32_t euid;
+	uint32_t gid;
+	uint32_t egid;
+	uint32_t unused;
+};
+
+static struct vdso_identity identity;
+
+// Reads the identity page, or fails with `EAGAIN` if the page is invalid.
+static int read_identity(void)
+{
+	const volatile struct vdso_identity *page;
+	uintptr_t addr;
+	uint64_t generation;
+
+	addr = getauxval(AT_SYSINFO_EHDR) - VDSO_TEXT_OFFSET +
+	       VDSO_IDENTITY_OFFSET;
+	page = (const volatile struct vdso_identity *)addr;
+
+	generation = page->generation;
+	__sync_synchronize();
+	identity.pid = page->pid;
+	identity.uid = page->uid;
+	identity.euid = page->euid;
+	identity.gid = page->gid;
+	identity.egid = page->egid;
+	__sync_synchronize();
+
+	if (generation == 0 || generation % 2 != 0 ||
+	    generation != page->generation) {
+		errno = EAGAIN;
+		return -1;
+	}
+	identity.generation = generation;
+	return 0;
+}
+
+// Checks the identity page against the results of the system calls.
+static int is_identity_correct(void)
+{
+	return read_identity() == 0 && identity.pid == syscall(SYS_getpid) &&
+	       identity.uid == syscall(SYS_getuid) &&
+	       identity.euid == syscall(SYS_geteuid) &&
+	       identity.gid == syscall(SYS_getgid) &&
+	       identity.egid == syscall(SYS_getegid);
+}
+
+static int wait_for_child(pid_t pid)
+{
+	int status;
+
+	if (waitpid(pid, &status, 0) != pid)
+		return -1;
+	return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+FN_TEST(identity)
+{
+	TEST_RES(getauxval(AT_SYSINFO_EHDR), _ret != 0);
+	TEST_RES(is_identity_correct(), _ret);
+}
+END_TEST()
+
+FN_TEST(fork)
+{
+	uint32_t parent_pid;
+	pid_t pid;
+
+	TEST_SUCC(read_identity());
+	parent_pid = identity.pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (!is_identity_correct() || identity.pid == parent_pid)
+			_exit(EXIT_FAILURE);
+		_exit(EXIT_SUCCESS);
+	}
+	TEST_RES(wait_for_child(pid), _ret == EXIT_SUCCESS);
+
+	// The page of the parent is not affected by the child.
+	TEST_RES(is_identity_correct(), _ret && identity.pid == parent_pid);
+}
+END_TEST()
+
+static int change_ids(void)
+{
+	uint64_t generation;
+
+	if (read_identity() < 0)
+		return -1;
+	generation = identity.generation;
+
+	if (setresgid(1000, 1001, 1002) < 0 || !is_identity_correct() ||
+	    identity.gid != 1000 || identity.egid != 1001 ||
+	    identity.generation <= generation)
+		return -1;
+	generation = identity.generation;
+
+	if (setresuid(2000, 2001, 2002) < 0 || !is_identity_correct() ||
+	    identity.uid != 2000 || identity.euid != 2001 ||
+	    identity.generation <= generation)
+		return -1;
+	generation = identity.generation;
+
+	if (setreuid(-1, 2000) < 0 || !is_identity_correct() ||
+	    identity.euid != 2000 || identity.generation <= generation)
+		return -1;
+
+	return 0;
+}
+
+FN_TEST(set_ids)
+{
+	pid_t pid;
+
+	// Changes the IDs in a child to keep the parent privileged.
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(change_ids() == 0 ? EXIT_SUCCESS : EXIT_FAILURE);
+	TEST_RES(wait_for_child(pid), _ret == EXIT_SUCCESS);
+}
+END_TEST()
+
+static int share_vm(void)
+{
+	pid_t pid;
+
+	if (!is_identity_correct())
+		return -1;
+
+	// The page cannot describe both processes sharing the address space.
+	pid = vfork();
+	if (pid == 0)
+		_exit(EXIT_SUCCESS);
+	if (pid < 0 || wait_for_child(pid) != EXIT_SUCCESS)
+		return -1;
+
+	return read_identity() < 0 && errno == EAGAIN ? 0 : -1;
+}
+
+FN_TEST(vfork)
+{
+	pid_t pid;
+
+	// Shares the address space in a child to keep the page of the parent valid.
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(share_vm() == 0 ? EXIT_SUCCESS : EXIT_FAILURE);
+	TEST_RES(wait_for_child(pid), _ret == EXIT_SUCCESS);
+
+	TEST_RES(is_identity_correct(), _ret);
+}
+END_TEST()
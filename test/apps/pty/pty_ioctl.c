@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include "../network/test.h"
+
+#include <unistd.h>
+#include <pty.h>
+#include <signal.h>
+#include <string.h>
+#include <termios.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+
+// The `struct termios2` in Linux, which conflicts with `<termios.h>`
+struct ktermios2 {
+	tcflag_t c_iflag;
+	tcflag_t c_oflag;
+	tcflag_t c_cflag;
+	tcflag_t c_lflag;
+	cc_t c_line;
+	cc_t c_cc[19];
+	speed_t c_ispeed;
+	speed_t c_ospeed;
+};
+
+#define KTCGETS2 _IOR('T', 0x2A, struct ktermios2)
+#define KTCSETS2 _IOW('T', 0x2B, struct ktermios2)
+#define KBOTHER 0010000
+
+static int master, slave;
+static volatile int nr_winch;
+
+static void handle_winch(int sig)
+{
+	nr_winch++;
+}
+
+FN_SETUP(openpty)
+{
+	CHECK(openpty(&master, &slave, NULL, NULL, NULL));
+}
+END_SETUP()
+
+FN_SETUP(run_in_new_session)
+{
+	int status;
+
+	if (CHECK(fork()) != 0) {
+		CHECK_WITH(wait(&status),
+			   WIFEXITED(status) && WEXITSTATUS(status) == 0);
+		exit(EXIT_SUCCESS);
+	}
+
+	CHECK(setsid());
+	CHECK(ioctl(slave, TIOCSCTTY, 0));
+	signal(SIGWINCH, handle_winch);
+}
+END_SETUP()
+
+FN_SETUP(raw_mode)
+{
+	struct termios term;
+
+	CHECK(tcgetattr(slave, &term));
+	cfmakeraw(&term);
+	CHECK(tcsetattr(slave, TCSANOW, &term));
+}
+END_SETUP()
+
+FN_TEST(winsize)
+{
+	struct winsize ws = { .ws_row = 24, .ws_col = 80 };
+	struct winsize ws2;
+
+	TEST_RES(ioctl(master, TIOCSWINSZ, &ws), nr_winch == 1);
+	TEST_RES(ioctl(slave, TIOCGWINSZ, &ws2),
+		 ws2.ws_row == 24 && ws2.ws_col == 80);
+
+	// Setting the same window size sends no signals
+	TEST_RES(ioctl(slave, TIOCSWINSZ, &ws), nr_winch == 1);
+
+	ws.ws_col = 132;
+	TEST_RES(ioctl(slave, TIOCSWINSZ, &ws), nr_winch == 2);
+}
+END_TEST()
+
+FN_TEST(steal_own_tty)
+{
+	TEST_SUCC(ioctl(slave, TIOCSCTTY, 1));
+	TEST_SUCC(ioctl(master, TIOCSCTTY, 0));
+}
+END_TEST()
+
+FN_TEST(termios2)
+{
+	struct ktermios2 term2;
+	struct termios term;
+
+	TEST_RES(ioctl(slave, KTCGETS2, &term2),
+		 term2.c_ispeed == 38400 && term2.c_ospeed == 38400);
+
+	term2.c_cflag = (term2.c_cflag & ~CBAUD) | KBOTHER;
+	term2.c_ispeed = term2.c_ospeed = 9600;
+	TEST_SUCC(ioctl(slave, KTCSETS2, &term2));
+	TEST_RES(tcgetattr(slave, &term), cfgetospeed(&term) == B9600);
+
+	term2.c_ispeed = term2.c_ospeed = 12345;
+	TEST_ERRNO(ioctl(slave, KTCSETS2, &term2), EINVAL);
+}
+END_TEST()
+
+FN_TEST(output_processing)
+{
+	struct termios term;
+	char buf[16];
+
+	TEST_SUCC(tcgetattr(slave, &term));
+	term.c_oflag |= OPOST | ONLCR;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+
+	TEST_RES(write(slave, "a\n", 2), _ret == 2);
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 3 && memcmp(buf, "a\r\n", 3) == 0);
+
+	term.c_oflag &= ~OPOST;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+
+	TEST_RES(write(slave, "a\n", 2), _ret == 2);
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 2 && memcmp(buf, "a\n", 2) == 0);
+}
+END_TEST()
+
+FN_TEST(flush_input)
+{
+	int len;
+
+	TEST_RES(write(master, "abc", 3), _ret == 3);
+	TEST_RES(ioctl(slave, FIONREAD, &len), len == 3);
+
+	TEST_SUCC(tcflush(slave, TCIFLUSH));
+	TEST_RES(ioctl(slave, FIONREAD, &len), len == 0);
+
+	TEST_ERRNO(ioctl(slave, TCFLSH, 5), EINVAL);
+}
+END_TEST()
+
+FN_TEST(vmin_vtime)
+{
+	struct termios term;
+	char buf[16];
+
+	TEST_SUCC(tcgetattr(slave, &term));
+	term.c_cc[VMIN] = 0;
+	term.c_cc[VTIME] = 1;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+
+	// The read times out after 0.1 seconds
+	TEST_RES(read(slave, buf, sizeof(buf)), _ret == 0);
+
+	TEST_RES(write(master, "ab", 2), _ret == 2);
+	TEST_RES(read(slave, buf, sizeof(buf)),
+		 _ret == 2 && memcmp(buf, "ab", 2) == 0);
+
+	term.c_cc[VMIN] = 3;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+
+	// The read returns the available bytes after 0.1 seconds
+	TEST_RES(write(master, "ab", 2), _ret == 2);
+	TEST_RES(read(slave, buf, sizeof(buf)),
+		 _ret == 2 && memcmp(buf, "ab", 2) == 0);
+}
+END_TEST()
+
+FN_TEST(unknown_ioctl)
+{
+	TEST_ERRNO(ioctl(slave, _IO('T', 0xff)), ENOTTY);
+}
+END_TEST()
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdlib.h>
+#include <string.h>
+#include <termios.h>
+#include <unistd.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+
+static int master, slave;
+
+FN_SETUP(open_master)
+{
+	master = CHECK(posix_openpt(O_RDWR | O_NOCTTY));
+}
+END_SETUP()
+
+FN_TEST(lock)
+{
+	int locked;
+	char name[32];
+
+	TEST_RES(ioctl(master, TIOCGPTLCK, &locked), locked == 1);
+	TEST_SUCC(ptsname_r(master, name, sizeof(name)));
+	TEST_ERRNO(open(name, O_RDWR | O_NOCTTY), EIO);
+	TEST_ERRNO(ioctl(master, TIOCGPTPEER, O_RDWR | O_NOCTTY), EIO);
+
+	TEST_SUCC(unlockpt(master));
+	TEST_RES(ioctl(master, TIOCGPTLCK, &locked), locked == 0);
+}
+END_TEST()
+
+FN_SETUP(open_slave)
+{
+	struct termios term;
+
+	slave = CHECK(ioctl(master, TIOCGPTPEER, O_RDWR | O_NOCTTY | O_CLOEXEC));
+	CHECK_WITH(fcntl(slave, F_GETFD), _ret == FD_CLOEXEC);
+
+	CHECK(tcgetattr(slave, &term));
+	cfmakeraw(&term);
+	CHECK(tcsetattr(slave, TCSANOW, &term));
+}
+END_SETUP()
+
+FN_TEST(packet_mode)
+{
+	int mode = 1;
+	char buf[16];
+
+	TEST_SUCC(ioctl(master, TIOCPKT, &mode));
+	TEST_RES(ioctl(master, TIOCGPKT, &mode), mode == 1);
+
+	// The data is preceded by `TIOCPKT_DATA`
+	TEST_RES(write(slave, "ab", 2), _ret == 2);
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 3 && buf[0] == TIOCPKT_DATA &&
+			 memcmp(buf + 1, "ab", 2) == 0);
+
+	// The status is read as a single byte
+	TEST_RES(write(slave, "ab", 2), _ret == 2);
+	TEST_SUCC(tcflush(slave, TCIOFLUSH));
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 1 &&
+			 buf[0] == (TIOCPKT_FLUSHREAD | TIOCPKT_FLUSHWRITE));
+
+	mode = 0;
+	TEST_SUCC(ioctl(master, TIOCPKT, &mode));
+	TEST_RES(write(slave, "ab", 2), _ret == 2);
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 2 && memcmp(buf, "ab", 2) == 0);
+}
+END_TEST()
+
+FN_TEST(packet_mode_stop)
+{
+	int mode = 1;
+	struct termios term;
+	struct pollfd pfd = { .fd = master, .events = POLLIN | POLLPRI };
+	char buf[16];
+
+	TEST_SUCC(ioctl(master, TIOCPKT, &mode));
+
+	TEST_SUCC(tcgetattr(slave, &term));
+	term.c_iflag |= IXON;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && (pfd.revents & POLLPRI));
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 1 && buf[0] == TIOCPKT_DOSTOP);
+
+	term.c_iflag &= ~IXON;
+	TEST_SUCC(tcsetattr(slave, TCSANOW, &term));
+	TEST_RES(read(master, buf, sizeof(buf)),
+		 _ret == 1 && buf[0] == TIOCPKT_NOSTOP);
+
+	mode = 0;
+	TEST_SUCC(ioctl(master, TIOCPKT, &mode));
+}
+END_TEST()
+
+FN_TEST(slave_only_ioctls)
+{
+	int mode;
+
+	TEST_ERRNO(ioctl(slave, TIOCGPKT, &mode), ENOTTY);
+	TEST_ERRNO(ioctl(slave, TIOCGPTLCK, &mode), ENOTTY);
+}
+END_TEST()
+
+FN_TEST(hang_up)
+{
+	int status, fds[2];
+	pid_t pid;
+	char buf[16];
+
+	TEST_SUCC(pipe(fds));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		CHECK(setsid());
+		CHECK(ioctl(slave, TIOCSCTTY, 0));
+		CHECK(write(fds[1], "x", 1));
+		pause();
+		exit(EXIT_FAILURE);
+	}
+
+	// Wait until the child gets the controlling terminal
+	TEST_RES(read(fds[0], buf, 1), _ret == 1);
+	TEST_SUCC(close(fds[0]));
+	TEST_SUCC(close(fds[1]));
+
+	TEST_SUCC(close(master));
+	TEST_RES(wait(&status), _ret == pid && WIFSIGNALED(status) &&
+					WTERMSIG(status) == SIGHUP);
+
+	TEST_RES(read(slave, buf, sizeof(buf)), _ret == 0);
+	TEST_ERRNO(write(slave, "a", 1), EIO);
+	TEST_SUCC(close(slave));
+}
+END_TEST()
@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/quota.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define MNT_DIR "/ext2"
+#define QUOTA_FILE MNT_DIR "/aquota.user"
+#define BASE_DIR MNT_DIR "/quota_test"
+#define TEST_UID 4242
+
+#define QFMT_VFS_V1 4
+#define QUOTA_BLOCK_SIZE 1024
+#define GRACE_TIME (7 * 24 * 3600)
+
+static struct if_dqblk dqblk;
+static struct if_nextdqblk nextdqblk;
+static struct if_dqinfo dqinfo;
+
+static int create_quota_file(void)
+{
+	// The header and the empty radix tree of the `QFMT_VFS_V1` format.
+	uint32_t header[8] = {
+		0xd9c01f11, // Magic
+		1, // Version
+		GRACE_TIME, // Space grace period
+		GRACE_TIME, // Inode grace period
+		0, // Flags
+		2, // Number of blocks
+		0, // First free block
+		0, // First block with free entries
+	};
+	char buf[QUOTA_BLOCK_SIZE * 2] = {};
+	int fd;
+
+	memcpy(buf, header, sizeof(header));
+	fd = open(QUOTA_FILE, O_CREAT | O_TRUNC | O_WRONLY, 0600);
+	if (fd < 0) {
+		return -1;
+	}
+	if (write(fd, buf, sizeof(buf)) != sizeof(buf)) {
+		close(fd);
+		return -1;
+	}
+
+	return close(fd);
+}
+
+FN_SETUP(quota_file)
+{
+	CHECK(create_quota_file());
+	CHECK(mkdir(BASE_DIR, 0777));
+	CHECK(chmod(BASE_DIR, 0777));
+	CHECK(close(CHECK(open(BASE_DIR "/f1", O_CREAT | O_WRONLY, 0644))));
+}
+END_SETUP()
+
+FN_TEST(quota_on)
+{
+	uint32_t format;
+
+	TEST_ERRNO(quotactl(QCMD(Q_GETFMT, USRQUOTA), MNT_DIR, 0,
+			    (caddr_t)&format),
+		   ESRCH);
+	TEST_ERRNO(quotactl(QCMD(Q_QUOTAON, USRQUOTA), MNT_DIR, 1,
+			    (caddr_t)QUOTA_FILE),
+		   ESRCH);
+	TEST_ERRNO(quotactl(QCMD(Q_QUOTAON, GRPQUOTA), MNT_DIR, QFMT_VFS_V1,
+			    (caddr_t)QUOTA_FILE),
+		   EINVAL);
+	TEST_ERRNO(quotactl(QCMD(Q_QUOTAON, USRQUOTA), MNT_DIR, QFMT_VFS_V1,
+			    (caddr_t)"/tmp"),
+		   EXDEV);
+
+	TEST_SUCC(quotactl(QCMD(Q_QUOTAON, USRQUOTA), MNT_DIR, QFMT_VFS_V1,
+			   (caddr_t)QUOTA_FILE));
+	TEST_ERRNO(quotactl(QCMD(Q_QUOTAON, USRQUOTA), MNT_DIR, QFMT_VFS_V1,
+			    (caddr_t)QUOTA_FILE),
+		   EBUSY);
+
+	TEST_RES(quotactl(QCMD(Q_GETFMT, USRQUOTA), MNT_DIR, 0,
+			  (caddr_t)&format),
+		 format == QFMT_VFS_V1);
+	TEST_RES(quotactl(QCMD(Q_GETINFO, USRQUOTA), MNT_DIR, 0,
+			  (caddr_t)&dqinfo),
+		 dqinfo.dqi_bgrace == GRACE_TIME &&
+			 dqinfo.dqi_igrace == GRACE_TIME);
+}
+END_TEST()
+
+FN_TEST(set_quota)
+{
+	memset(&dqblk, 0, sizeof(dqblk));
+	dqblk.dqb_bhardlimit = 64;
+	dqblk.dqb_ihardlimit = 3;
+	dqblk.dqb_valid = QIF_LIMITS;
+	TEST_SUCC(quotactl(QCMD(Q_SETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			   (caddr_t)&dqblk));
+
+	TEST_RES(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			  (caddr_t)&dqblk),
+		 dqblk.dqb_bhardlimit == 64 && dqblk.dqb_ihardlimit == 3 &&
+			 dqblk.dqb_curinodes == 0 && dqblk.dqb_curspace == 0);
+
+	// The usage is transferred to the new owner.
+	TEST_SUCC(chown(BASE_DIR "/f1", TEST_UID, -1));
+	TEST_RES(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			  (caddr_t)&dqblk),
+		 dqblk.dqb_curinodes == 1);
+
+	TEST_RES(quotactl(QCMD(Q_GETNEXTQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			  (caddr_t)&nextdqblk),
+		 nextdqblk.dqb_id == TEST_UID &&
+			 nextdqblk.dqb_bhardlimit == 64);
+	TEST_ERRNO(quotactl(QCMD(Q_GETNEXTQUOTA, USRQUOTA), MNT_DIR,
+			    TEST_UID + 1, (caddr_t)&nextdqblk),
+		   ENOENT);
+}
+END_TEST()
+
+FN_TEST(exceed_limits)
+{
+	char buf[4096] = {};
+	int status;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		int fd, i;
+		ssize_t ret = 0;
+
+		CHECK(setuid(TEST_UID));
+
+		// Only the quotas of the current user can be queried.
+		CHECK(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			       (caddr_t)&dqblk));
+		if (quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, 0,
+			     (caddr_t)&dqblk) != -1 ||
+		    errno != EPERM) {
+			_exit(1);
+		}
+
+		fd = CHECK(open(BASE_DIR "/f1", O_WRONLY));
+		for (i = 0; i < 32; i++) {
+			ret = write(fd, buf, sizeof(buf));
+			if (ret < 0) {
+				break;
+			}
+		}
+		if (ret != -1 || errno != EDQUOT || i > 16) {
+			_exit(2);
+		}
+		CHECK(close(fd));
+
+		CHECK(close(CHECK(open(BASE_DIR "/f2", O_CREAT | O_WRONLY,
+				       0644))));
+		CHECK(close(CHECK(open(BASE_DIR "/f3", O_CREAT | O_WRONLY,
+				       0644))));
+		if (open(BASE_DIR "/f4", O_CREAT | O_WRONLY, 0644) != -1 ||
+		    errno != EDQUOT) {
+			_exit(3);
+		}
+
+		_exit(0);
+	}
+
+	TEST_RES(wait(&status), _ret == pid && WIFEXITED(status) &&
+					WEXITSTATUS(status) == 0);
+	TEST_RES(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			  (caddr_t)&dqblk),
+		 dqblk.dqb_curinodes == 3 &&
+			 dqblk.dqb_curspace <= 64 * QUOTA_BLOCK_SIZE);
+
+	// The usage is released when the files are removed.
+	TEST_SUCC(unlink(BASE_DIR "/f1"));
+	TEST_SUCC(unlink(BASE_DIR "/f2"));
+	TEST_SUCC(unlink(BASE_DIR "/f3"));
+	TEST_RES(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			  (caddr_t)&dqblk),
+		 dqblk.dqb_curinodes == 0 && dqblk.dqb_curspace == 0);
+}
+END_TEST()
+
+FN_TEST(quota_off)
+{
+	int fd;
+
+	fd = TEST_SUCC(open(BASE_DIR, O_RDONLY | O_DIRECTORY));
+	TEST_SUCC(syscall(SYS_quotactl_fd, fd, QCMD(Q_SYNC, USRQUOTA), 0,
+			  NULL));
+	TEST_ERRNO(syscall(SYS_quotactl_fd, fd, QCMD(Q_QUOTAON, USRQUOTA),
+			   QFMT_VFS_V1, QUOTA_FILE),
+		   EINVAL);
+	TEST_SUCC(syscall(SYS_quotactl_fd, fd, QCMD(Q_QUOTAOFF, USRQUOTA), 0,
+			  NULL));
+	TEST_SUCC(close(fd));
+
+	TEST_ERRNO(quotactl(QCMD(Q_GETQUOTA, USRQUOTA), MNT_DIR, TEST_UID,
+			    (caddr_t)&dqblk),
+		   ESRCH);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(rmdir(BASE_DIR));
+	CHECK(unlink(QUOTA_FILE));
+}
+END_SETUP()
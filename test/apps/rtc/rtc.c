@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <linux/rtc.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+
+#include "../network/test.h"
+
+#define RTC_PATH "/dev/rtc0"
+#define RTC_ALIAS_PATH "/dev/rtc"
+
+static int rtc_fd;
+static struct rtc_time old_time;
+
+FN_SETUP(open)
+{
+	rtc_fd = CHECK(open(RTC_PATH, O_RDONLY));
+	CHECK(ioctl(rtc_fd, RTC_RD_TIME, &old_time));
+}
+END_SETUP()
+
+FN_TEST(device_node)
+{
+	struct stat stat_buf;
+	struct stat alias_stat_buf;
+
+	TEST_RES(stat(RTC_PATH, &stat_buf), S_ISCHR(stat_buf.st_mode));
+	TEST_RES(stat(RTC_ALIAS_PATH, &alias_stat_buf),
+		 alias_stat_buf.st_rdev == stat_buf.st_rdev);
+}
+END_TEST()
+
+FN_TEST(exclusive_open)
+{
+	TEST_ERRNO(open(RTC_PATH, O_RDONLY), EBUSY);
+	TEST_ERRNO(open(RTC_ALIAS_PATH, O_RDONLY), EBUSY);
+}
+END_TEST()
+
+FN_TEST(read_time)
+{
+	struct rtc_time time;
+
+	TEST_RES(ioctl(rtc_fd, RTC_RD_TIME, &time),
+		 time.tm_year >= 70 && time.tm_mon >= 0 && time.tm_mon < 12 &&
+			 time.tm_mday >= 1 && time.tm_mday <= 31 &&
+			 time.tm_hour >= 0 && time.tm_hour < 24 &&
+			 time.tm_wday >= 0 && time.tm_wday < 7);
+}
+END_TEST()
+
+FN_TEST(set_time)
+{
+	// 2030-01-01 is a Tuesday.
+	struct rtc_time time = {
+		.tm_year = 130,
+		.tm_mon = 0,
+		.tm_mday = 1,
+		.tm_hour = 12,
+		.tm_min = 0,
+		.tm_sec = 0,
+	};
+
+	TEST_SUCC(ioctl(rtc_fd, RTC_SET_TIME, &time));
+	TEST_RES(ioctl(rtc_fd, RTC_RD_TIME, &time),
+		 time.tm_year == 130 && time.tm_mon == 0 &&
+			 time.tm_mday == 1 && time.tm_hour == 12 &&
+			 time.tm_min == 0 && time.tm_wday == 2 &&
+			 time.tm_yday == 0);
+
+	TEST_SUCC(ioctl(rtc_fd, RTC_SET_TIME, &old_time));
+}
+END_TEST()
+
+FN_TEST(set_invalid_time)
+{
+	struct rtc_time time = old_time;
+
+	time.tm_mon = 12;
+	TEST_ERRNO(ioctl(rtc_fd, RTC_SET_TIME, &time), EINVAL);
+
+	time = old_time;
+	time.tm_mon = 1;
+	time.tm_mday = 30;
+	TEST_ERRNO(ioctl(rtc_fd, RTC_SET_TIME, &time), EINVAL);
+
+	time = old_time;
+	time.tm_hour = 24;
+	TEST_ERRNO(ioctl(rtc_fd, RTC_SET_TIME, &time), EINVAL);
+}
+END_TEST()
+
+FN_TEST(unsupported)
+{
+	char buf[sizeof(unsigned long)];
+
+	// The update interrupts are not supported, so the users should poll the time instead.
+	TEST_ERRNO(ioctl(rtc_fd, RTC_UIE_ON, 0), ENOTTY);
+	TEST_ERRNO(read(rtc_fd, buf, sizeof(buf)), EOPNOTSUPP);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(rtc_fd));
+
+	// The RTC can be opened again after it is closed.
+	rtc_fd = CHECK(open(RTC_ALIAS_PATH, O_RDONLY));
+	CHECK(close(rtc_fd));
+}
+END_SETUP()
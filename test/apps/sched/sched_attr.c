@@ -51,6 +51,34 @@ FN_TEST(sched_param)
 }
 END_TEST()
 
+FN_TEST(sched_rr_interval)
+{
+	struct sched_param param = { .sched_priority = 50 };
+	struct timespec interval;
+
+	TEST_ERRNO(sched_rr_get_interval(-100, &interval), EINVAL);
+	TEST_ERRNO(sched_rr_get_interval(1234567890, &interval), ESRCH);
+	TEST_ERRNO(sched_rr_get_interval(0, NULL), EFAULT);
+
+	// FIFO threads have no time slice.
+	TEST_RES(sched_setscheduler(0, SCHED_FIFO, &param), _ret == 0);
+	TEST_RES(sched_rr_get_interval(0, &interval),
+		 _ret == 0 && interval.tv_sec == 0 && interval.tv_nsec == 0);
+	TEST_SUCC(sched_yield());
+
+	TEST_RES(sched_setscheduler(0, SCHED_RR, &param), _ret == 0);
+	TEST_RES(sched_rr_get_interval(0, &interval),
+		 _ret == 0 && (interval.tv_sec > 0 || interval.tv_nsec > 0));
+	TEST_SUCC(sched_yield());
+
+	param.sched_priority = 0;
+	TEST_RES(sched_setscheduler(0, SCHED_OTHER, &param), _ret == 0);
+	// The time slice of fair threads varies with the load.
+	TEST_SUCC(sched_rr_get_interval(0, &interval));
+	TEST_SUCC(sched_yield());
+}
+END_TEST()
+
 FN_TEST(sched_prio_limit)
 {
 	TEST_ERRNO(sched_get_priority_max(-100), EINVAL);
@@ -63,5 +63,34 @@ echo "All fdatasync test passed."
 
 pipe/pipe_err
 pipe/short_rw
+pipe/splice
+copy_file_range/copy_file_range
+block/queue
+block/partition
+block/discard
+chattr/chattr
+file_io/close_range
+file_io/seek_hole
+file_lock/file_lock
+ext2/ext2
+fallocate/fallocate
+fanotify/fanotify
+fuse/fuse
+io_uring/io_uring
+loop/loop
+dm/dm
+evdev/evdev
+hvc/hvc
+irq/irq_affinity
+rtc/rtc
+watchdog/watchdog
+sound/pcm
+mount/mount
+openat2/openat2
+quota/quota
+statfs/statfs
+statx/statx
+utimensat/utimensat
+xattr/xattr
 epoll/epoll_err
 epoll/poll_err
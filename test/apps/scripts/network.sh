@@ -31,10 +31,25 @@ sleep 0.2
 ./send_buf_full
 ./tcp_err
 ./tcp_poll
+./tcp_backlog
 ./udp_err
+./udp_mmsg
+./ping
+./timestamping
 ./unix_err
+./unix_scm
+./unix_seqpacket
+./packet_socket
+./ipv6
 
 ./netlink_route
+./route
+./proc_connector
 ./rtnl_err
+./nftables
+./bridge
+./tun
+./ip_frag
+./wireguard
 
 echo "All network test passed"
@@ -10,6 +10,8 @@ cd ${SCRIPT_DIR}/..
 echo "Start process test......"
 # These test programs are sorted by name.
 tests="
+clock/clock_settime
+clock/vdso
 clone3/clone_exit_signal
 clone3/clone_files
 clone3/clone_no_exit_signal
@@ -21,22 +23,38 @@ exit/exit_procfs
 eventfd2/eventfd2
 fork/fork
 fork_c/fork
+futex/futex_waitv
 getpid/getpid
+getrandom/getrandom
 hello_pie/hello
 hello_world/hello_world
+itimer/posix_timer
 itimer/setitimer
 itimer/timer_create
+itimer/timerfd
+membarrier/membarrier
 mmap/mmap_and_fork
 mmap/mmap_shared_filebacked
 mmap/mmap_readahead
+prctl/sched_core
 process/group_session
 process/job_control
+process/proc_config
+process/proc_mem
+process/proc_pid
+process/syscall_deny
+process/vdso_identity
 pthread/pthread_test
 pty/open_pty
+pty/pty_ioctl
+pty/pty_packet
 sched/sched_attr
 shm/posix_shm
 signal_c/parent_death_signal
+signal_c/sigmask_wait
+signal_c/signal_fpu
 signal_c/signal_test
+signal_c/signalfd
 "
 
 for testcase in ${tests}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <poll.h>
+#include <signal.h>
+#include <unistd.h>
+#include <sys/select.h>
+#include <sys/syscall.h>
+
+static volatile int nr_usr1;
+
+static void handle_usr1(int sig)
+{
+	nr_usr1++;
+}
+
+static sigset_t blocked, unblocked;
+
+FN_SETUP(block_usr1)
+{
+	signal(SIGUSR1, handle_usr1);
+
+	sigemptyset(&blocked);
+	sigaddset(&blocked, SIGUSR1);
+	CHECK(sigprocmask(SIG_BLOCK, &blocked, NULL));
+	CHECK(sigprocmask(SIG_BLOCK, NULL, &unblocked));
+	sigdelset(&unblocked, SIGUSR1);
+}
+END_SETUP()
+
+static int is_usr1_blocked(void)
+{
+	sigset_t mask;
+
+	sigprocmask(SIG_BLOCK, NULL, &mask);
+	return sigismember(&mask, SIGUSR1);
+}
+
+FN_TEST(ppoll_sigmask)
+{
+	struct timespec ts = { .tv_sec = 10 };
+
+	TEST_SUCC(kill(getpid(), SIGUSR1));
+	TEST_RES(nr_usr1, _ret == 0);
+
+	TEST_ERRNO(ppoll(NULL, 0, &ts, &unblocked), EINTR);
+	TEST_RES(nr_usr1, _ret == 1);
+	TEST_RES(is_usr1_blocked(), _ret);
+}
+END_TEST()
+
+FN_TEST(pselect_sigmask)
+{
+	struct timespec ts = { .tv_sec = 10 };
+
+	TEST_SUCC(kill(getpid(), SIGUSR1));
+	TEST_RES(nr_usr1, _ret == 1);
+
+	TEST_ERRNO(pselect(0, NULL, NULL, NULL, &ts, &unblocked), EINTR);
+	TEST_RES(nr_usr1, _ret == 2);
+	TEST_RES(is_usr1_blocked(), _ret);
+}
+END_TEST()
+
+FN_TEST(sigsuspend_sigmask)
+{
+	TEST_SUCC(kill(getpid(), SIGUSR1));
+	TEST_RES(nr_usr1, _ret == 2);
+
+	TEST_ERRNO(sigsuspend(&unblocked), EINTR);
+	TEST_RES(nr_usr1, _ret == 3);
+	TEST_RES(is_usr1_blocked(), _ret);
+}
+END_TEST()
+
+FN_TEST(select_remaining_timeout)
+{
+	struct timeval tv = { .tv_sec = 0, .tv_usec = 100000 };
+
+	TEST_RES(select(0, NULL, NULL, NULL, &tv),
+		 _ret == 0 && tv.tv_sec == 0 && tv.tv_usec == 0);
+
+	// A zero timeout is not written back
+	TEST_RES(select(0, NULL, NULL, NULL, &tv), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(ppoll_remaining_timeout)
+{
+	struct timespec ts = { .tv_sec = 0, .tv_nsec = 100000000 };
+	int fds[2];
+	struct pollfd pfd;
+
+	TEST_SUCC(pipe(fds));
+	TEST_SUCC(write(fds[1], "a", 1));
+
+	// The raw syscall is used because the glibc wrapper hides the remaining time
+	pfd.fd = fds[0];
+	pfd.events = POLLIN;
+	TEST_RES(syscall(SYS_ppoll, &pfd, 1, &ts, NULL, 0),
+		 _ret == 1 && ts.tv_sec == 0 && ts.tv_nsec > 0 &&
+			 ts.tv_nsec <= 100000000);
+
+	TEST_SUCC(close(fds[0]));
+	TEST_SUCC(close(fds[1]));
+}
+END_TEST()
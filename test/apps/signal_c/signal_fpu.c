@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <signal.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/syscall.h>
+#include <ucontext.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define NR_XMM 16
+// The register that the signal handler changes in the signal frame.
+#define MODIFIED_XMM 7
+
+// Rounds towards zero with all exceptions masked, which differs from the
+// default MXCSR (0x1f80).
+#define MXCSR_IN 0x7f80
+// Uses the double precision, which differs from the default x87 control word
+// (0x037f).
+#define FCW_IN 0x027f
+
+struct fpu_state {
+	uint8_t xmm[NR_XMM][16];
+	uint32_t mxcsr;
+	uint16_t fcw;
+};
+
+static struct fpu_state state_in __attribute__((aligned(16)));
+static struct fpu_state state_out __attribute__((aligned(16)));
+static uint8_t modified_xmm[16];
+
+static volatile int nr_signals;
+static volatile int is_frame_correct;
+
+#define LOAD_XMM(n) "movdqu " #n "*16(%[in]), %%xmm" #n "\n\t"
+#define STORE_XMM(n) "movdqu %%xmm" #n ", " #n "*16(%[out])\n\t"
+#define FOR_EACH_XMM(f)                                                       \
+	f(0) f(1) f(2) f(3) f(4) f(5) f(6) f(7) f(8) f(9) f(10) f(11) f(12)   \
+		f(13) f(14) f(15)
+
+// Loads `state_in` into the FPU, sends a signal to the current process, and
+// stores the FPU state after the signal is handled into `state_out`.
+//
+// Everything is done in a single inline assembly block, so the compiler
+// cannot touch the FPU in the meantime.
+static long kill_self_with_fpu_state(int sig)
+{
+	long ret;
+
+	asm volatile(FOR_EACH_XMM(LOAD_XMM)
+		     "ldmxcsr %c[mxcsr](%[in])\n\t"
+		     "fldcw %c[fcw](%[in])\n\t"
+		     "syscall\n\t"
+		     FOR_EACH_XMM(STORE_XMM)
+		     "stmxcsr %c[mxcsr](%[out])\n\t"
+		     "fnstcw %c[fcw](%[out])\n\t"
+		     : "=a"(ret)
+		     : "a"(SYS_kill), "D"(getpid()), "S"(sig),
+		       [in] "r"(&state_in), [out] "r"(&state_out),
+		       [mxcsr] "i"(offsetof(struct fpu_state, mxcsr)),
+		       [fcw] "i"(offsetof(struct fpu_state, fcw))
+		     : "rcx", "r11", "memory", "xmm0", "xmm1", "xmm2", "xmm3",
+		       "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+		       "xmm11", "xmm12", "xmm13", "xmm14", "xmm15");
+
+	return ret;
+}
+
+static void clobber_fpu(void)
+{
+	static const uint32_t mxcsr = 0x1f80;
+
+	asm volatile("pcmpeqd %%xmm0, %%xmm0\n\t"
+		     "pcmpeqd %%xmm1, %%xmm1\n\t"
+		     "pcmpeqd %%xmm2, %%xmm2\n\t"
+		     "pcmpeqd %%xmm3, %%xmm3\n\t"
+		     "pcmpeqd %%xmm4, %%xmm4\n\t"
+		     "pcmpeqd %%xmm5, %%xmm5\n\t"
+		     "pcmpeqd %%xmm6, %%xmm6\n\t"
+		     "pcmpeqd %%xmm7, %%xmm7\n\t"
+		     "pcmpeqd %%xmm8, %%xmm8\n\t"
+		     "pcmpeqd %%xmm9, %%xmm9\n\t"
+		     "pcmpeqd %%xmm10, %%xmm10\n\t"
+		     "pcmpeqd %%xmm11, %%xmm11\n\t"
+		     "pcmpeqd %%xmm12, %%xmm12\n\t"
+		     "pcmpeqd %%xmm13, %%xmm13\n\t"
+		     "pcmpeqd %%xmm14, %%xmm14\n\t"
+		     "pcmpeqd %%xmm15, %%xmm15\n\t"
+		     "ldmxcsr %[mxcsr]\n\t"
+		     "fninit\n\t"
+		     :
+		     : [mxcsr] "m"(mxcsr)
+		     : "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6",
+		       "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13",
+		       "xmm14", "xmm15");
+}
+
+static void handler(int sig, siginfo_t *info, void *ucontext)
+{
+	fpregset_t fpregs = ((ucontext_t *)ucontext)->uc_mcontext.fpregs;
+
+	// The FPU state of the interrupted code is saved in the signal frame.
+	is_frame_correct = fpregs != NULL &&
+			   memcmp(fpregs->_xmm, state_in.xmm,
+				  sizeof(state_in.xmm)) == 0 &&
+			   fpregs->mxcsr == MXCSR_IN && fpregs->cwd == FCW_IN;
+
+	// The changes to the signal frame are restored by `rt_sigreturn`.
+	if (fpregs != NULL)
+		memcpy(&fpregs->_xmm[MODIFIED_XMM], modified_xmm,
+		       sizeof(modified_xmm));
+
+	clobber_fpu();
+	nr_signals++;
+}
+
+FN_SETUP(init)
+{
+	struct sigaction sa = { .sa_sigaction = handler,
+				.sa_flags = SA_SIGINFO };
+	int i, j;
+
+	for (i = 0; i < NR_XMM; i++)
+		for (j = 0; j < 16; j++)
+			state_in.xmm[i][j] = i * 16 + j + 1;
+	state_in.mxcsr = MXCSR_IN;
+	state_in.fcw = FCW_IN;
+
+	for (j = 0; j < 16; j++)
+		modified_xmm[j] = 0xa5 ^ j;
+
+	CHECK(sigaction(SIGUSR1, &sa, NULL));
+}
+END_SETUP()
+
+static int is_xmm_restored(void)
+{
+	int i;
+
+	for (i = 0; i < NR_XMM; i++) {
+		const uint8_t *expected = i == MODIFIED_XMM ? modified_xmm :
+							      state_in.xmm[i];
+
+		if (memcmp(state_out.xmm[i], expected, 16) != 0)
+			return 0;
+	}
+	return 1;
+}
+
+FN_TEST(restore_after_sigreturn)
+{
+	TEST_RES(kill_self_with_fpu_state(SIGUSR1), _ret == 0);
+	TEST_RES(nr_signals, _ret == 1);
+	TEST_RES(is_frame_correct, _ret);
+
+	TEST_RES(is_xmm_restored(), _ret);
+	TEST_RES(state_out.mxcsr, _ret == MXCSR_IN);
+	TEST_RES(state_out.fcw, _ret == FCW_IN);
+}
+END_TEST()
+
+FN_TEST(restore_repeatedly)
+{
+	// The FPU state is restored every time, not only after the first signal
+	// of the thread.
+	TEST_RES(kill_self_with_fpu_state(SIGUSR1), _ret == 0);
+	TEST_RES(kill_self_with_fpu_state(SIGUSR1), _ret == 0);
+	TEST_RES(nr_signals, _ret == 3);
+	TEST_RES(is_xmm_restored() && state_out.mxcsr == MXCSR_IN &&
+			 state_out.fcw == FCW_IN,
+		 _ret);
+}
+END_TEST()
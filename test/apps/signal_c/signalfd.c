@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <signal.h>
+#include <unistd.h>
+#include <sys/signalfd.h>
+
+FN_TEST(signalfd_siginfo)
+{
+	int sfd;
+	sigset_t mask, old_mask;
+	struct signalfd_siginfo info;
+
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR1);
+	TEST_SUCC(sigprocmask(SIG_BLOCK, &mask, &old_mask));
+
+	sfd = TEST_SUCC(signalfd(-1, &mask, SFD_NONBLOCK));
+	TEST_ERRNO(read(sfd, &info, sizeof(info)), EAGAIN);
+
+	TEST_SUCC(kill(getpid(), SIGUSR1));
+	TEST_RES(read(sfd, &info, sizeof(info)),
+		 _ret == sizeof(info) && info.ssi_signo == SIGUSR1 &&
+			 info.ssi_code == SI_USER &&
+			 info.ssi_pid == getpid() && info.ssi_uid == getuid());
+	TEST_ERRNO(read(sfd, &info, sizeof(info)), EAGAIN);
+
+	TEST_SUCC(close(sfd));
+	TEST_SUCC(sigprocmask(SIG_SETMASK, &old_mask, NULL));
+}
+END_TEST()
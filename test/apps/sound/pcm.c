@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <limits.h>
+#include <string.h>
+#include <unistd.h>
+#include <sound/asound.h>
+#include <sys/ioctl.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define PCM_PATH "/dev/snd/pcmC0D0p"
+
+#define CHANNELS 2
+#define RATE 48000
+#define PERIOD_SIZE 1024
+#define PERIODS 4
+#define FRAME_BYTES (CHANNELS * 2)
+#define BUFFER_SIZE (PERIOD_SIZE * PERIODS)
+
+static int pcm_fd;
+static short frames[PERIOD_SIZE * CHANNELS];
+
+static struct snd_interval *
+hw_param_interval(struct snd_pcm_hw_params *params, int param)
+{
+	return &params->intervals[param - SNDRV_PCM_HW_PARAM_FIRST_INTERVAL];
+}
+
+static void set_hw_param_mask(struct snd_pcm_hw_params *params, int param,
+			      unsigned int value)
+{
+	memset(&params->masks[param], 0, sizeof(params->masks[param]));
+	params->masks[param].bits[value / 32] = 1U << (value & 31);
+}
+
+static void set_hw_param_interval(struct snd_pcm_hw_params *params, int param,
+				  unsigned int value)
+{
+	hw_param_interval(params, param)->min = value;
+	hw_param_interval(params, param)->max = value;
+}
+
+static void init_hw_params(struct snd_pcm_hw_params *params, int access)
+{
+	int i;
+
+	memset(params, 0, sizeof(*params));
+	for (i = SNDRV_PCM_HW_PARAM_FIRST_MASK;
+	     i <= SNDRV_PCM_HW_PARAM_LAST_MASK; i++)
+		memset(&params->masks[i], 0xff, sizeof(params->masks[i]));
+	for (i = SNDRV_PCM_HW_PARAM_FIRST_INTERVAL;
+	     i <= SNDRV_PCM_HW_PARAM_LAST_INTERVAL; i++)
+		hw_param_interval(params, i)->max = UINT_MAX;
+	params->rmask = ~0U;
+
+	set_hw_param_mask(params, SNDRV_PCM_HW_PARAM_ACCESS, access);
+	set_hw_param_mask(params, SNDRV_PCM_HW_PARAM_FORMAT,
+			  SNDRV_PCM_FORMAT_S16_LE);
+	set_hw_param_interval(params, SNDRV_PCM_HW_PARAM_CHANNELS, CHANNELS);
+	set_hw_param_interval(params, SNDRV_PCM_HW_PARAM_RATE, RATE);
+	set_hw_param_interval(params, SNDRV_PCM_HW_PARAM_PERIOD_SIZE,
+			      PERIOD_SIZE);
+	set_hw_param_interval(params, SNDRV_PCM_HW_PARAM_PERIODS, PERIODS);
+}
+
+static unsigned int get_hw_param(struct snd_pcm_hw_params *params, int param)
+{
+	return hw_param_interval(params, param)->min;
+}
+
+FN_SETUP(open)
+{
+	pcm_fd = CHECK(open(PCM_PATH, O_RDWR));
+}
+END_SETUP()
+
+FN_TEST(device_node)
+{
+	struct stat stat_buf;
+
+	TEST_RES(stat(PCM_PATH, &stat_buf),
+		 S_ISCHR(stat_buf.st_mode) && major(stat_buf.st_rdev) == 116 &&
+			 minor(stat_buf.st_rdev) == 16);
+}
+END_TEST()
+
+FN_TEST(exclusive_open)
+{
+	TEST_ERRNO(open(PCM_PATH, O_RDWR), EBUSY);
+}
+END_TEST()
+
+FN_TEST(info)
+{
+	int version;
+	struct snd_pcm_info info;
+
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_PVERSION, &version),
+		 version >= SNDRV_PROTOCOL_VERSION(2, 0, 0));
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_INFO, &info),
+		 info.card == 0 && info.device == 0 &&
+			 info.stream == SNDRV_PCM_STREAM_PLAYBACK &&
+			 info.subdevices_count == 1);
+}
+END_TEST()
+
+FN_TEST(not_set_up)
+{
+	TEST_ERRNO(ioctl(pcm_fd, SNDRV_PCM_IOCTL_PREPARE), EBADFD);
+	TEST_ERRNO(write(pcm_fd, frames, sizeof(frames)), EBADFD);
+}
+END_TEST()
+
+FN_TEST(hw_params)
+{
+	struct snd_pcm_hw_params params;
+
+	init_hw_params(&params, SNDRV_PCM_ACCESS_RW_INTERLEAVED);
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_HW_REFINE, &params),
+		 params.masks[SNDRV_PCM_HW_PARAM_FORMAT].bits[0] ==
+			 1U << SNDRV_PCM_FORMAT_S16_LE);
+
+	init_hw_params(&params, SNDRV_PCM_ACCESS_RW_INTERLEAVED);
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_HW_PARAMS, &params),
+		 get_hw_param(&params, SNDRV_PCM_HW_PARAM_CHANNELS) ==
+				 CHANNELS &&
+			 get_hw_param(&params, SNDRV_PCM_HW_PARAM_RATE) ==
+				 RATE &&
+			 get_hw_param(&params,
+				      SNDRV_PCM_HW_PARAM_PERIOD_SIZE) ==
+				 PERIOD_SIZE &&
+			 get_hw_param(&params,
+				      SNDRV_PCM_HW_PARAM_BUFFER_SIZE) ==
+				 BUFFER_SIZE &&
+			 get_hw_param(&params,
+				      SNDRV_PCM_HW_PARAM_BUFFER_BYTES) ==
+				 BUFFER_SIZE * FRAME_BYTES);
+}
+END_TEST()
+
+FN_TEST(sw_params)
+{
+	struct snd_pcm_sw_params params;
+
+	memset(&params, 0, sizeof(params));
+	params.avail_min = PERIOD_SIZE;
+	params.start_threshold = BUFFER_SIZE;
+	params.stop_threshold = BUFFER_SIZE;
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_SW_PARAMS, &params),
+		 params.boundary >= BUFFER_SIZE &&
+			 params.boundary / BUFFER_SIZE * BUFFER_SIZE ==
+				 params.boundary);
+
+	params.silence_size = PERIOD_SIZE;
+	TEST_ERRNO(ioctl(pcm_fd, SNDRV_PCM_IOCTL_SW_PARAMS, &params), EINVAL);
+}
+END_TEST()
+
+FN_TEST(write_and_drain)
+{
+	struct snd_pcm_sync_ptr sync_ptr;
+	struct snd_xferi xferi;
+	snd_pcm_sframes_t delay;
+
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_PREPARE));
+
+	TEST_ERRNO(write(pcm_fd, frames, FRAME_BYTES + 1), EINVAL);
+	TEST_RES(write(pcm_fd, frames, sizeof(frames)),
+		 _ret == sizeof(frames));
+
+	xferi.buf = frames;
+	xferi.frames = PERIOD_SIZE;
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_WRITEI_FRAMES, &xferi),
+		 xferi.result == PERIOD_SIZE);
+
+	memset(&sync_ptr, 0, sizeof(sync_ptr));
+	sync_ptr.flags = SNDRV_PCM_SYNC_PTR_APPL | SNDRV_PCM_SYNC_PTR_AVAIL_MIN;
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_SYNC_PTR, &sync_ptr),
+		 sync_ptr.s.status.state == SNDRV_PCM_STATE_PREPARED &&
+			 sync_ptr.s.status.hw_ptr == 0 &&
+			 sync_ptr.c.control.appl_ptr == 2 * PERIOD_SIZE);
+
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_DELAY, &delay),
+		 delay == 2 * PERIOD_SIZE);
+
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_DRAIN));
+
+	memset(&sync_ptr, 0, sizeof(sync_ptr));
+	sync_ptr.flags = SNDRV_PCM_SYNC_PTR_APPL | SNDRV_PCM_SYNC_PTR_AVAIL_MIN;
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_SYNC_PTR, &sync_ptr),
+		 sync_ptr.s.status.state == SNDRV_PCM_STATE_SETUP &&
+			 sync_ptr.s.status.hw_ptr == 2 * PERIOD_SIZE);
+}
+END_TEST()
+
+FN_TEST(start_and_drop)
+{
+	struct snd_pcm_sync_ptr sync_ptr;
+
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_PREPARE));
+	TEST_ERRNO(ioctl(pcm_fd, SNDRV_PCM_IOCTL_START), EPIPE);
+
+	TEST_RES(write(pcm_fd, frames, sizeof(frames)),
+		 _ret == sizeof(frames));
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_START));
+
+	memset(&sync_ptr, 0, sizeof(sync_ptr));
+	sync_ptr.flags = SNDRV_PCM_SYNC_PTR_APPL | SNDRV_PCM_SYNC_PTR_AVAIL_MIN;
+	TEST_RES(ioctl(pcm_fd, SNDRV_PCM_IOCTL_SYNC_PTR, &sync_ptr),
+		 sync_ptr.s.status.state == SNDRV_PCM_STATE_RUNNING ||
+			 sync_ptr.s.status.state == SNDRV_PCM_STATE_XRUN);
+
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_DROP));
+	TEST_ERRNO(ioctl(pcm_fd, SNDRV_PCM_IOCTL_START), EBADFD);
+}
+END_TEST()
+
+FN_TEST(mmap)
+{
+	struct snd_pcm_hw_params params;
+	void *addr;
+
+	// The buffer cannot be mapped with the read/write access.
+	TEST_ERRNO((long)mmap(NULL, BUFFER_SIZE * FRAME_BYTES, PROT_WRITE,
+			      MAP_SHARED, pcm_fd, 0),
+		   EINVAL);
+
+	init_hw_params(&params, SNDRV_PCM_ACCESS_MMAP_INTERLEAVED);
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_HW_PARAMS, &params));
+
+	addr = (void *)TEST_SUCC((long)mmap(NULL, BUFFER_SIZE * FRAME_BYTES,
+					    PROT_READ | PROT_WRITE, MAP_SHARED,
+					    pcm_fd, 0));
+	memset(addr, 0, BUFFER_SIZE * FRAME_BYTES);
+	TEST_SUCC(munmap(addr, BUFFER_SIZE * FRAME_BYTES));
+
+	TEST_ERRNO((long)mmap(NULL, getpagesize(), PROT_READ, MAP_SHARED,
+			      pcm_fd, SNDRV_PCM_MMAP_OFFSET_STATUS),
+		   ENXIO);
+	TEST_ERRNO(write(pcm_fd, frames, sizeof(frames)), EINVAL);
+
+	TEST_SUCC(ioctl(pcm_fd, SNDRV_PCM_IOCTL_HW_FREE));
+}
+END_TEST()
+
+FN_SETUP(close)
+{
+	CHECK(close(pcm_fd));
+	pcm_fd = CHECK(open(PCM_PATH, O_RDWR));
+	CHECK(close(pcm_fd));
+}
+END_SETUP()
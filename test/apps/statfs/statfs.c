@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <linux/magic.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/statvfs.h>
+
+#include "../network/test.h"
+
+#define EXFAT_SUPER_MAGIC 0x2011BAB0
+
+#define OVL_DIR "/tmp/statfs_ovl"
+#define OVL_LOWER OVL_DIR "/lower"
+#define OVL_UPPER OVL_DIR "/upper"
+#define OVL_WORK OVL_DIR "/work"
+#define OVL_MERGED OVL_DIR "/merged"
+
+static struct statfs buf;
+
+FN_TEST(magic_numbers)
+{
+	TEST_RES(statfs("/", &buf), buf.f_type == RAMFS_MAGIC);
+	TEST_RES(statfs("/proc", &buf), buf.f_type == PROC_SUPER_MAGIC);
+	TEST_RES(statfs("/sys", &buf), buf.f_type == SYSFS_MAGIC);
+	TEST_RES(statfs("/dev/pts", &buf), buf.f_type == DEVPTS_SUPER_MAGIC);
+	TEST_RES(statfs("/ext2", &buf), buf.f_type == EXT2_SUPER_MAGIC);
+	TEST_RES(statfs("/exfat", &buf), buf.f_type == EXFAT_SUPER_MAGIC);
+}
+END_TEST()
+
+FN_TEST(block_counts)
+{
+	TEST_RES(statfs("/ext2", &buf),
+		 buf.f_bsize > 0 && buf.f_blocks > 0 &&
+			 buf.f_bfree <= buf.f_blocks &&
+			 buf.f_bavail <= buf.f_bfree && buf.f_files > 0 &&
+			 buf.f_ffree <= buf.f_files && buf.f_namelen == 255 &&
+			 (buf.f_fsid.__val[0] != 0 || buf.f_fsid.__val[1] != 0));
+	TEST_RES(statfs("/exfat", &buf),
+		 buf.f_bsize > 0 && buf.f_blocks > 0 &&
+			 buf.f_bfree <= buf.f_blocks &&
+			 buf.f_bavail <= buf.f_bfree && buf.f_namelen == 255);
+	TEST_RES(statfs("/", &buf),
+		 buf.f_blocks > 0 && buf.f_bfree <= buf.f_blocks &&
+			 buf.f_namelen == 255);
+}
+END_TEST()
+
+FN_TEST(fstatfs)
+{
+	struct statfs fd_buf;
+	int fd;
+
+	fd = TEST_SUCC(open("/ext2", O_RDONLY | O_DIRECTORY));
+	TEST_SUCC(statfs("/ext2", &buf));
+	TEST_RES(fstatfs(fd, &fd_buf),
+		 fd_buf.f_type == buf.f_type &&
+			 fd_buf.f_blocks == buf.f_blocks &&
+			 fd_buf.f_fsid.__val[0] == buf.f_fsid.__val[0] &&
+			 fd_buf.f_fsid.__val[1] == buf.f_fsid.__val[1]);
+	TEST_SUCC(close(fd));
+
+	TEST_ERRNO(fstatfs(-1, &buf), EBADF);
+}
+END_TEST()
+
+FN_TEST(mount_flags)
+{
+	struct statvfs vfs_buf;
+
+	TEST_SUCC(mkdir(OVL_DIR, 0755));
+	TEST_SUCC(mkdir(OVL_LOWER, 0755));
+	TEST_SUCC(mkdir(OVL_UPPER, 0755));
+	TEST_SUCC(mkdir(OVL_WORK, 0755));
+	TEST_SUCC(mkdir(OVL_MERGED, 0755));
+
+	TEST_SUCC(mount("overlay", OVL_MERGED, "overlay", MS_NOSUID | MS_NODEV,
+			"lowerdir=" OVL_LOWER ",upperdir=" OVL_UPPER
+			",workdir=" OVL_WORK));
+
+	TEST_RES(statfs(OVL_MERGED, &buf),
+		 buf.f_type == OVERLAYFS_SUPER_MAGIC &&
+			 (buf.f_flags & ST_NOSUID) && (buf.f_flags & ST_NODEV) &&
+			 !(buf.f_flags & ST_RDONLY));
+	TEST_RES(statvfs(OVL_MERGED, &vfs_buf),
+		 (vfs_buf.f_flag & ST_NOSUID) && (vfs_buf.f_flag & ST_NODEV));
+	TEST_RES(statfs(OVL_DIR, &buf), !(buf.f_flags & ST_NOSUID));
+
+	TEST_SUCC(umount(OVL_MERGED));
+	TEST_SUCC(rmdir(OVL_MERGED));
+	TEST_SUCC(rmdir(OVL_WORK "/work"));
+	TEST_SUCC(rmdir(OVL_WORK));
+	TEST_SUCC(rmdir(OVL_UPPER));
+	TEST_SUCC(rmdir(OVL_LOWER));
+	TEST_SUCC(rmdir(OVL_DIR));
+}
+END_TEST()
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/stat.h>
+
+#include "../network/test.h"
+
+#ifndef STATX_MNT_ID
+#define STATX_MNT_ID 0x00001000U
+#endif
+
+#ifndef STATX_ATTR_MOUNT_ROOT
+#define STATX_ATTR_MOUNT_ROOT 0x00002000
+#endif
+
+#define FILE_NAME "/tmp/statx_test_file"
+
+static int file_fd;
+
+FN_SETUP(file)
+{
+	file_fd = CHECK(open(FILE_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	CHECK_WITH(write(file_fd, "0123456789", 10), _ret == 10);
+}
+END_SETUP()
+
+FN_TEST(basic_stats)
+{
+	struct statx stx;
+	struct stat st;
+
+	TEST_SUCC(fstat(file_fd, &st));
+	TEST_RES(statx(AT_FDCWD, FILE_NAME, 0, STATX_BASIC_STATS, &stx),
+		 (stx.stx_mask & STATX_BASIC_STATS) == STATX_BASIC_STATS &&
+			 stx.stx_size == 10 && stx.stx_ino == st.st_ino &&
+			 stx.stx_mtime.tv_sec == st.st_mtim.tv_sec &&
+			 stx.stx_mtime.tv_nsec == st.st_mtim.tv_nsec);
+}
+END_TEST()
+
+FN_TEST(btime)
+{
+	struct statx stx;
+
+	TEST_RES(statx(AT_FDCWD, FILE_NAME, 0, STATX_BTIME, &stx),
+		 (stx.stx_mask & STATX_BTIME) && stx.stx_btime.tv_sec != 0);
+
+	// The birth time is not reported if it is not requested.
+	TEST_RES(statx(AT_FDCWD, FILE_NAME, 0, STATX_BASIC_STATS, &stx),
+		 !(stx.stx_mask & STATX_BTIME));
+}
+END_TEST()
+
+FN_TEST(mount_id_and_root)
+{
+	struct statx stx_root;
+	struct statx stx;
+
+	TEST_RES(statx(AT_FDCWD, "/", 0, STATX_MNT_ID, &stx_root),
+		 (stx_root.stx_mask & STATX_MNT_ID) &&
+			 stx_root.stx_mnt_id != 0 &&
+			 (stx_root.stx_attributes_mask &
+			  STATX_ATTR_MOUNT_ROOT) &&
+			 (stx_root.stx_attributes & STATX_ATTR_MOUNT_ROOT));
+
+	TEST_RES(statx(AT_FDCWD, FILE_NAME, 0, STATX_MNT_ID, &stx),
+		 (stx.stx_mask & STATX_MNT_ID) && stx.stx_mnt_id != 0 &&
+			 (stx.stx_attributes_mask & STATX_ATTR_MOUNT_ROOT) &&
+			 !(stx.stx_attributes & STATX_ATTR_MOUNT_ROOT));
+}
+END_TEST()
+
+FN_TEST(empty_path)
+{
+	struct statx stx;
+	int fildes[2];
+
+	TEST_RES(statx(file_fd, "", AT_EMPTY_PATH, STATX_BASIC_STATS, &stx),
+		 stx.stx_size == 10);
+	TEST_ERRNO(statx(file_fd, "", 0, STATX_BASIC_STATS, &stx), ENOENT);
+
+	// Files that are not related to an inode have the basic stats as well.
+	CHECK(pipe(fildes));
+	TEST_RES(statx(fildes[0], "", AT_EMPTY_PATH, STATX_BASIC_STATS, &stx),
+		 S_ISFIFO(stx.stx_mode) && !(stx.stx_mask & STATX_MNT_ID));
+	CHECK(close(fildes[0]));
+	CHECK(close(fildes[1]));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(file_fd));
+	CHECK(unlink(FILE_NAME));
+}
+END_SETUP()
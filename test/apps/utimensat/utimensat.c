@@ -0,0 +1,279 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <time.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define TEST_FILE "/tmp/utimensat_test_file"
+#define EXT2_FILE "/ext2/utimensat_test_file"
+
+#define OVL_DIR "/tmp/utimensat_ovl"
+#define OVL_LOWER OVL_DIR "/lower"
+#define OVL_UPPER OVL_DIR "/upper"
+#define OVL_WORK OVL_DIR "/work"
+#define OVL_MERGED OVL_DIR "/merged"
+#define OVL_FILE OVL_MERGED "/file"
+
+static struct stat st;
+
+static int ts_eq(const struct timespec *a, const struct timespec *b)
+{
+	return a->tv_sec == b->tv_sec && a->tv_nsec == b->tv_nsec;
+}
+
+static int ts_lt(const struct timespec *a, const struct timespec *b)
+{
+	return a->tv_sec < b->tv_sec ||
+	       (a->tv_sec == b->tv_sec && a->tv_nsec < b->tv_nsec);
+}
+
+static int read_one_byte(const char *path, int flags)
+{
+	char buf;
+	int fd;
+
+	fd = open(path, O_RDONLY | flags);
+	if (fd < 0)
+		return -1;
+	if (read(fd, &buf, 1) < 0) {
+		close(fd);
+		return -1;
+	}
+	return close(fd);
+}
+
+static int create_file(const char *path)
+{
+	int fd;
+
+	fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+	if (fd < 0)
+		return -1;
+	if (write(fd, "a", 1) != 1) {
+		close(fd);
+		return -1;
+	}
+	return close(fd);
+}
+
+FN_SETUP(init)
+{
+	CHECK(create_file(TEST_FILE));
+	CHECK(create_file(EXT2_FILE));
+}
+END_SETUP()
+
+FN_TEST(nanoseconds)
+{
+	struct timespec times[2] = { { 1, 123456789 }, { 2, 987654321 } };
+
+	TEST_SUCC(utimensat(AT_FDCWD, TEST_FILE, times, 0));
+	TEST_RES(stat(TEST_FILE, &st), ts_eq(&st.st_atim, &times[0]) &&
+					       ts_eq(&st.st_mtim, &times[1]));
+
+	TEST_SUCC(utimensat(AT_FDCWD, EXT2_FILE, times, 0));
+	TEST_RES(stat(EXT2_FILE, &st), ts_eq(&st.st_atim, &times[0]) &&
+					       ts_eq(&st.st_mtim, &times[1]));
+}
+END_TEST()
+
+FN_TEST(utime_now_and_omit)
+{
+	struct timespec times[2] = { { 1, 0 }, { 2, 0 } };
+	struct timespec before, old_ctime;
+
+	TEST_SUCC(utimensat(AT_FDCWD, TEST_FILE, times, 0));
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME_COARSE, &before));
+
+	times[0].tv_nsec = UTIME_OMIT;
+	times[1].tv_nsec = UTIME_NOW;
+	TEST_SUCC(utimensat(AT_FDCWD, TEST_FILE, times, 0));
+	TEST_RES(stat(TEST_FILE, &st), st.st_atim.tv_sec == 1 &&
+					       !ts_lt(&st.st_mtim, &before) &&
+					       !ts_lt(&st.st_ctim, &before));
+
+	// Omitting both timestamps changes nothing, not even the change time.
+	old_ctime = st.st_ctim;
+	times[0].tv_nsec = UTIME_OMIT;
+	times[1].tv_nsec = UTIME_OMIT;
+	TEST_SUCC(usleep(20 * 1000));
+	TEST_SUCC(utimensat(AT_FDCWD, TEST_FILE, times, 0));
+	TEST_RES(stat(TEST_FILE, &st), ts_eq(&st.st_ctim, &old_ctime));
+
+	times[0].tv_nsec = 1000000000;
+	TEST_ERRNO(utimensat(AT_FDCWD, TEST_FILE, times, 0), EINVAL);
+	times[0].tv_nsec = -1;
+	TEST_ERRNO(utimensat(AT_FDCWD, TEST_FILE, times, 0), EINVAL);
+}
+END_TEST()
+
+FN_TEST(null_and_empty_paths)
+{
+	struct timespec times[2] = { { 3, 0 }, { 4, 0 } };
+	int fd;
+
+	fd = TEST_SUCC(open(TEST_FILE, O_RDONLY));
+
+	TEST_SUCC(futimens(fd, times));
+	TEST_RES(stat(TEST_FILE, &st),
+		 st.st_atim.tv_sec == 3 && st.st_mtim.tv_sec == 4);
+
+	// The C library does not allow NULL paths, so use the raw system calls.
+	TEST_ERRNO(syscall(SYS_utimensat, fd, NULL, times, AT_SYMLINK_NOFOLLOW),
+		   EINVAL);
+	TEST_ERRNO(syscall(SYS_utimensat, AT_FDCWD, NULL, times, 0), EFAULT);
+
+	TEST_ERRNO(utimensat(fd, "", times, 0), ENOENT);
+	times[1].tv_sec = 5;
+	TEST_SUCC(utimensat(fd, "", times, AT_EMPTY_PATH));
+	TEST_RES(stat(TEST_FILE, &st), st.st_mtim.tv_sec == 5);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(change_time)
+{
+	struct timespec old_ctime;
+
+	TEST_SUCC(stat(TEST_FILE, &st));
+	old_ctime = st.st_ctim;
+	TEST_SUCC(usleep(20 * 1000));
+	TEST_SUCC(chmod(TEST_FILE, 0600));
+	TEST_RES(stat(TEST_FILE, &st), ts_lt(&old_ctime, &st.st_ctim));
+
+	TEST_SUCC(stat(EXT2_FILE, &st));
+	old_ctime = st.st_ctim;
+	TEST_SUCC(usleep(20 * 1000));
+	TEST_SUCC(chmod(EXT2_FILE, 0600));
+	TEST_RES(stat(EXT2_FILE, &st), ts_lt(&old_ctime, &st.st_ctim));
+}
+END_TEST()
+
+FN_TEST(permissions)
+{
+	struct timespec times[2] = { { 1, 0 }, { 2, 0 } };
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(chmod(TEST_FILE, 0644));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (setuid(65534) < 0)
+			_exit(1);
+		// Only the owner can set arbitrary timestamps.
+		if (utimensat(AT_FDCWD, TEST_FILE, times, 0) >= 0 ||
+		    errno != EPERM)
+			_exit(2);
+		// Setting the current time requires the write permission.
+		if (utimensat(AT_FDCWD, TEST_FILE, NULL, 0) >= 0 ||
+		    errno != EACCES)
+			_exit(3);
+		_exit(0);
+	}
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	TEST_SUCC(chmod(TEST_FILE, 0666));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (setuid(65534) < 0)
+			_exit(1);
+		if (utimensat(AT_FDCWD, TEST_FILE, NULL, 0) < 0)
+			_exit(2);
+		_exit(0);
+	}
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_TEST(strict_atime)
+{
+	struct timespec times[2] = { { 1, 0 }, { UTIME_OMIT, UTIME_OMIT } };
+
+	TEST_SUCC(utimensat(AT_FDCWD, TEST_FILE, times, 0));
+	TEST_SUCC(read_one_byte(TEST_FILE, O_NOATIME));
+	TEST_RES(stat(TEST_FILE, &st), st.st_atim.tv_sec == 1);
+	TEST_SUCC(read_one_byte(TEST_FILE, 0));
+	TEST_RES(stat(TEST_FILE, &st), st.st_atim.tv_sec != 1);
+}
+END_TEST()
+
+FN_SETUP(mount_overlay)
+{
+	CHECK(mkdir(OVL_DIR, 0755));
+	CHECK(mkdir(OVL_LOWER, 0755));
+	CHECK(mkdir(OVL_UPPER, 0755));
+	CHECK(mkdir(OVL_WORK, 0755));
+	CHECK(mkdir(OVL_MERGED, 0755));
+}
+END_SETUP()
+
+FN_TEST(relatime)
+{
+	struct timespec times[2] = { { 1, 0 }, { 2, 0 } };
+
+	TEST_SUCC(mount("overlay", OVL_MERGED, "overlay", 0,
+			"lowerdir=" OVL_LOWER ",upperdir=" OVL_UPPER
+			",workdir=" OVL_WORK));
+	TEST_SUCC(create_file(OVL_FILE));
+
+	// The access time is older than the modification time, so it is updated.
+	TEST_SUCC(utimensat(AT_FDCWD, OVL_FILE, times, 0));
+	TEST_SUCC(read_one_byte(OVL_FILE, 0));
+	TEST_RES(stat(OVL_FILE, &st), st.st_atim.tv_sec > 2);
+
+	// The access time is newer than the modification and change times, and it
+	// is not older than one day, so it is not updated.
+	times[0].tv_sec = time(NULL) + 60;
+	TEST_SUCC(utimensat(AT_FDCWD, OVL_FILE, times, 0));
+	TEST_SUCC(read_one_byte(OVL_FILE, 0));
+	TEST_RES(stat(OVL_FILE, &st), st.st_atim.tv_sec == times[0].tv_sec);
+
+	TEST_SUCC(unlink(OVL_FILE));
+	TEST_SUCC(umount(OVL_MERGED));
+}
+END_TEST()
+
+FN_TEST(noatime)
+{
+	struct timespec times[2] = { { 1, 0 }, { 2, 0 } };
+
+	TEST_SUCC(mount("overlay", OVL_MERGED, "overlay", MS_NOATIME,
+			"lowerdir=" OVL_LOWER ",upperdir=" OVL_UPPER
+			",workdir=" OVL_WORK));
+	TEST_SUCC(create_file(OVL_FILE));
+
+	TEST_SUCC(utimensat(AT_FDCWD, OVL_FILE, times, 0));
+	TEST_SUCC(read_one_byte(OVL_FILE, 0));
+	TEST_RES(stat(OVL_FILE, &st), st.st_atim.tv_sec == 1);
+
+	TEST_SUCC(unlink(OVL_FILE));
+	TEST_SUCC(umount(OVL_MERGED));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(rmdir(OVL_MERGED));
+	CHECK(rmdir(OVL_WORK "/work"));
+	CHECK(rmdir(OVL_WORK));
+	CHECK(rmdir(OVL_UPPER));
+	CHECK(rmdir(OVL_LOWER));
+	CHECK(rmdir(OVL_DIR));
+
+	CHECK(unlink(TEST_FILE));
+	CHECK(unlink(EXT2_FILE));
+}
+END_SETUP()
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <unistd.h>
+#include <linux/watchdog.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+
+#include "../network/test.h"
+
+#define WATCHDOG_PATH "/dev/watchdog"
+#define WATCHDOG0_PATH "/dev/watchdog0"
+
+static int watchdog_fd;
+
+FN_SETUP(open)
+{
+	watchdog_fd = CHECK(open(WATCHDOG_PATH, O_WRONLY));
+}
+END_SETUP()
+
+FN_TEST(device_node)
+{
+	struct stat stat_buf;
+
+	TEST_RES(stat(WATCHDOG_PATH, &stat_buf),
+		 S_ISCHR(stat_buf.st_mode) && major(stat_buf.st_rdev) == 10 &&
+			 minor(stat_buf.st_rdev) == 130);
+	TEST_RES(stat(WATCHDOG0_PATH, &stat_buf), S_ISCHR(stat_buf.st_mode));
+}
+END_TEST()
+
+FN_TEST(exclusive_open)
+{
+	// `/dev/watchdog` is the same device as `/dev/watchdog0`.
+	TEST_ERRNO(open(WATCHDOG_PATH, O_WRONLY), EBUSY);
+	TEST_ERRNO(open(WATCHDOG0_PATH, O_WRONLY), EBUSY);
+}
+END_TEST()
+
+FN_TEST(get_support)
+{
+	struct watchdog_info info;
+
+	TEST_RES(ioctl(watchdog_fd, WDIOC_GETSUPPORT, &info),
+		 (info.options & WDIOF_SETTIMEOUT) &&
+			 (info.options & WDIOF_MAGICCLOSE) &&
+			 (info.options & WDIOF_KEEPALIVEPING) &&
+			 info.identity[0] != '\0');
+}
+END_TEST()
+
+FN_TEST(timeout)
+{
+	int timeout;
+
+	timeout = 0;
+	TEST_ERRNO(ioctl(watchdog_fd, WDIOC_SETTIMEOUT, &timeout), EINVAL);
+	timeout = -1;
+	TEST_ERRNO(ioctl(watchdog_fd, WDIOC_SETTIMEOUT, &timeout), EINVAL);
+
+	timeout = 20;
+	TEST_RES(ioctl(watchdog_fd, WDIOC_SETTIMEOUT, &timeout), timeout == 20);
+	timeout = 0;
+	TEST_RES(ioctl(watchdog_fd, WDIOC_GETTIMEOUT, &timeout), timeout == 20);
+}
+END_TEST()
+
+FN_TEST(keepalive)
+{
+	TEST_SUCC(ioctl(watchdog_fd, WDIOC_KEEPALIVE, 0));
+	TEST_RES(write(watchdog_fd, "ping", 4), _ret == 4);
+	TEST_RES(write(watchdog_fd, "", 0), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(magic_close)
+{
+	int status;
+
+	TEST_RES(write(watchdog_fd, "V", 1), _ret == 1);
+	TEST_RES(ioctl(watchdog_fd, WDIOC_GETSTATUS, &status),
+		 status & WDIOF_MAGICCLOSE);
+
+	// The magic character is forgotten after the next write.
+	TEST_RES(write(watchdog_fd, "x", 1), _ret == 1);
+	TEST_RES(ioctl(watchdog_fd, WDIOC_GETSTATUS, &status),
+		 !(status & WDIOF_MAGICCLOSE));
+}
+END_TEST()
+
+FN_TEST(set_options)
+{
+	int options;
+
+	options = 0;
+	TEST_ERRNO(ioctl(watchdog_fd, WDIOC_SETOPTIONS, &options), EINVAL);
+
+	options = WDIOS_DISABLECARD;
+	TEST_SUCC(ioctl(watchdog_fd, WDIOC_SETOPTIONS, &options));
+	options = WDIOS_ENABLECARD;
+	TEST_SUCC(ioctl(watchdog_fd, WDIOC_SETOPTIONS, &options));
+}
+END_TEST()
+
+FN_TEST(unknown_ioctl)
+{
+	int temperature;
+
+	TEST_ERRNO(ioctl(watchdog_fd, WDIOC_GETTEMP, &temperature), ENOTTY);
+}
+END_TEST()
+
+FN_TEST(reopen)
+{
+	// The watchdog is stopped after the magic close, so it can be opened again.
+	TEST_RES(write(watchdog_fd, "V", 1), _ret == 1);
+	TEST_SUCC(close(watchdog_fd));
+
+	watchdog_fd = TEST_SUCC(open(WATCHDOG0_PATH, O_WRONLY));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	int options = WDIOS_DISABLECARD;
+
+	CHECK(ioctl(watchdog_fd, WDIOC_SETOPTIONS, &options));
+	CHECK(write(watchdog_fd, "V", 1));
+	CHECK(close(watchdog_fd));
+}
+END_SETUP()
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/xattr.h>
+#include <linux/types.h>
+
+#include "../network/test.h"
+
+#ifndef SYS_setxattrat
+#define SYS_setxattrat 463
+#define SYS_getxattrat 464
+#define SYS_listxattrat 465
+#define SYS_removexattrat 466
+#endif
+
+struct xattr_args {
+	__u64 value;
+	__u32 size;
+	__u32 flags;
+};
+
+#define FILE_NAME "/tmp/xattr_test_file"
+#define FIFO_NAME "/tmp/xattr_test_fifo"
+
+static int file_fd;
+
+FN_SETUP(file)
+{
+	file_fd = CHECK(open(FILE_NAME, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	unlink(FIFO_NAME);
+	CHECK(mkfifo(FIFO_NAME, 0644));
+}
+END_SETUP()
+
+FN_TEST(invalid_names)
+{
+	TEST_ERRNO(setxattr(FILE_NAME, "user.", "v", 1, 0), EINVAL);
+	TEST_ERRNO(setxattr(FILE_NAME, "foo.bar", "v", 1, 0), EOPNOTSUPP);
+	TEST_ERRNO(setxattr(FILE_NAME, "system.posix_acl_access", "v", 1, 0),
+		   EOPNOTSUPP);
+	TEST_ERRNO(setxattr("", "user.foo", "v", 1, 0), ENOENT);
+}
+END_TEST()
+
+FN_TEST(user_xattr_on_fifo)
+{
+	char buf[8];
+
+	TEST_ERRNO(setxattr(FIFO_NAME, "user.foo", "v", 1, 0), EPERM);
+	TEST_ERRNO(getxattr(FIFO_NAME, "user.foo", buf, sizeof(buf)), ENODATA);
+	TEST_ERRNO(removexattr(FIFO_NAME, "user.foo"), EPERM);
+
+	// Other namespaces are not limited to regular files and directories.
+	TEST_SUCC(setxattr(FIFO_NAME, "trusted.foo", "v", 1, 0));
+	TEST_RES(getxattr(FIFO_NAME, "trusted.foo", buf, sizeof(buf)),
+		 _ret == 1 && buf[0] == 'v');
+	TEST_SUCC(removexattr(FIFO_NAME, "trusted.foo"));
+}
+END_TEST()
+
+FN_TEST(xattrat)
+{
+	struct xattr_args args;
+	char buf[16];
+
+	args.value = (__u64)(unsigned long)"value";
+	args.size = 5;
+	args.flags = XATTR_CREATE;
+	TEST_SUCC(syscall(SYS_setxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			  &args, sizeof(args)));
+	TEST_ERRNO(syscall(SYS_setxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			   &args, sizeof(args)),
+		   EEXIST);
+
+	memset(buf, 0, sizeof(buf));
+	args.value = (__u64)(unsigned long)buf;
+	args.size = sizeof(buf);
+	args.flags = 0;
+	TEST_RES(syscall(SYS_getxattrat, file_fd, "", AT_EMPTY_PATH, "user.at",
+			 &args, sizeof(args)),
+		 _ret == 5 && memcmp(buf, "value", 5) == 0);
+
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(syscall(SYS_listxattrat, file_fd, NULL, AT_EMPTY_PATH, buf,
+			 sizeof(buf)),
+		 _ret == 8 && strcmp(buf, "user.at") == 0);
+
+	TEST_SUCC(syscall(SYS_removexattrat, AT_FDCWD, FILE_NAME, 0,
+			  "user.at"));
+	TEST_ERRNO(syscall(SYS_removexattrat, AT_FDCWD, FILE_NAME, 0,
+			   "user.at"),
+		   ENODATA);
+}
+END_TEST()
+
+FN_TEST(xattrat_invalid_args)
+{
+	struct {
+		struct xattr_args args;
+		__u64 extra;
+	} ext_args;
+	char buf[8];
+
+	ext_args.args.value = (__u64)(unsigned long)buf;
+	ext_args.args.size = sizeof(buf);
+	ext_args.args.flags = 0;
+	ext_args.extra = 0;
+
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			   &ext_args, sizeof(struct xattr_args) - 1),
+		   EINVAL);
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, FILE_NAME, 0x1, "user.at",
+			   &ext_args, sizeof(ext_args)),
+		   EINVAL);
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, "", 0, "user.at",
+			   &ext_args, sizeof(ext_args)),
+		   ENOENT);
+
+	// Zeroed trailing fields are accepted.
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			   &ext_args, sizeof(ext_args)),
+		   ENODATA);
+
+	ext_args.extra = 1;
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			   &ext_args, sizeof(ext_args)),
+		   E2BIG);
+	ext_args.extra = 0;
+
+	ext_args.args.flags = XATTR_CREATE;
+	TEST_ERRNO(syscall(SYS_getxattrat, AT_FDCWD, FILE_NAME, 0, "user.at",
+			   &ext_args, sizeof(ext_args)),
+		   EINVAL);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(file_fd));
+	CHECK(unlink(FILE_NAME));
+	CHECK(unlink(FIFO_NAME));
+}
+END_SETUP()
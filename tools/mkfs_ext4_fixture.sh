@@ -0,0 +1,57 @@
+#!/bin/bash
+
+# SPDX-License-Identifier: MPL-2.0
+
+# Creates the Ext4 image used by the ktests of the Ext2 filesystem.
+#
+# The image contains:
+#  - `/extents`: a sparse file whose every even block is filled with the block index plus
+#    one, so the extents do not fit in the inode and the extent tree has a depth of one;
+#  - `/htree`: a directory with 500 empty files, which is indexed by the hashes of names;
+#  - `/bad_depth`: a file whose extent tree has an invalid depth;
+#  - `/bad_rec_len`: a directory whose first entry has a record length beyond the block.
+
+set -e
+
+if [ "$#" -ne 1 ]; then
+    echo "Usage: $0 <output_image>"
+    exit 1
+fi
+
+OUTPUT_IMAGE="$1"
+BLOCK_SIZE=4096
+ROOT_DIR=$(mktemp -d)
+TEMP_IMAGE=$(mktemp)
+trap 'rm -rf "$ROOT_DIR" "$TEMP_IMAGE"' EXIT
+
+for i in $(seq 0 2 18); do
+    head -c ${BLOCK_SIZE} /dev/zero \
+        | tr '\0' "\\$(printf '%03o' $((i + 1)))" \
+        | dd of="$ROOT_DIR/extents" bs=${BLOCK_SIZE} seek=$i conv=notrunc status=none
+done
+truncate -s $((24 * BLOCK_SIZE)) "$ROOT_DIR/extents"
+
+mkdir "$ROOT_DIR/htree"
+for i in $(seq -w 0 499); do
+    touch "$ROOT_DIR/htree/file_$i"
+done
+
+echo "bad_depth" > "$ROOT_DIR/bad_depth"
+mkdir "$ROOT_DIR/bad_rec_len"
+touch "$ROOT_DIR/bad_rec_len/victim"
+
+truncate -s 8M "$TEMP_IMAGE"
+mke2fs -q -t ext4 -b ${BLOCK_SIZE} -O ^metadata_csum -d "$ROOT_DIR" "$TEMP_IMAGE"
+# Builds the hash indexes of the directories.
+e2fsck -fyD "$TEMP_IMAGE" > /dev/null 2>&1 || [ $? -eq 1 ]
+
+# Sets the depth of the extent tree to 6, which is beyond the maximum depth of 5. The
+# second word of the block pointers holds the maximum number of entries and the depth.
+debugfs -w -R "sif /bad_depth block[1] 0x00060004" "$TEMP_IMAGE" 2> /dev/null
+# Sets the record length of the "." entry to twice the block size.
+DIR_BLOCK=$(debugfs -R "bmap /bad_rec_len 0" "$TEMP_IMAGE" 2> /dev/null)
+printf '\x00\x20' \
+    | dd of="$TEMP_IMAGE" bs=1 seek=$((DIR_BLOCK * BLOCK_SIZE + 4)) conv=notrunc status=none
+
+chmod 644 "$TEMP_IMAGE"
+mv "$TEMP_IMAGE" "$OUTPUT_IMAGE"
@@ -112,6 +112,8 @@ QEMU_ARGS="\
     -device virtio-net-pci,netdev=net01,disable-legacy=on,disable-modern=off$VIRTIO_NET_FEATURES$IOMMU_DEV_EXTRA \
     -device virtio-serial-pci,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     -device virtconsole,chardev=mux \
+    -audiodev none,id=snd0 \
+    -device virtio-sound-pci,audiodev=snd0,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     $IOMMU_EXTRA_ARGS \
 "
 